@@ -22,11 +22,17 @@ typedef uint64_t EFI_VIRTUAL_ADDRESS;
 // EFI status codes
 #define EFI_SUCCESS 0
 #define EFI_ERROR(x) ((EFI_STATUS)(0x8000000000000000ULL | (UINT64)(x)))
+#define EFI_LOAD_ERROR EFI_ERROR(1)
 #define EFI_INVALID_PARAMETER EFI_ERROR(2)
+#define EFI_VOLUME_CORRUPTED EFI_ERROR(10)
+#define EFI_NOT_FOUND EFI_ERROR(14)
 
 // EFI memory types
 #define EfiLoaderData 3
 
+// EFI handle search types
+#define ByProtocol 2
+
 // EFI reset types
 #define EfiResetCold 0
 
@@ -37,6 +43,21 @@ typedef struct _EFI_BOOT_SERVICES EFI_BOOT_SERVICES;
 typedef struct _EFI_RUNTIME_SERVICES EFI_RUNTIME_SERVICES;
 typedef struct _EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL;
 typedef struct _EFI_SIMPLE_TEXT_INPUT_PROTOCOL EFI_SIMPLE_TEXT_INPUT_PROTOCOL;
+typedef struct _EFI_BLOCK_IO_PROTOCOL EFI_BLOCK_IO_PROTOCOL;
+
+// EFI GUID
+typedef struct
+{
+    UINT32 Data1;
+    UINT16 Data2;
+    UINT16 Data3;
+    UINT8 Data4[8];
+} EFI_GUID;
+
+#define EFI_BLOCK_IO_PROTOCOL_GUID \
+    {0x964E5B21, 0x6459, 0x11D2, {0x8E, 0x39, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B}}
+#define EFI_PARTITION_INFO_PROTOCOL_GUID \
+    {0x8CF2F62C, 0xBC9B, 0x4821, {0x80, 0x8D, 0xEC, 0x9E, 0xC4, 0x21, 0xA1, 0xA0}}
 
 // EFI input key structure
 typedef struct
@@ -62,6 +83,10 @@ struct _EFI_BOOT_SERVICES
     EFI_STATUS (*AllocatePool)(UINT32 PoolType, UINTN Size, void **Buffer);
     EFI_STATUS (*WaitForEvent)(UINTN NumberOfEvents, void **Event, UINTN *Index);
     EFI_STATUS (*ResetSystem)(UINT32 ResetType, EFI_STATUS ResetStatus, UINTN DataSize, void *ResetData);
+    EFI_STATUS (*FreePool)(void *Buffer);
+    EFI_STATUS (*HandleProtocol)(EFI_HANDLE Handle, EFI_GUID *Protocol, void **Interface);
+    EFI_STATUS (*LocateHandleBuffer)(UINT32 SearchType, EFI_GUID *Protocol, void *SearchKey, UINTN *NoHandles,
+                                     EFI_HANDLE **Buffer);
 };
 
 // EFI runtime services structure
@@ -83,6 +108,56 @@ struct _EFI_SIMPLE_TEXT_INPUT_PROTOCOL
     void *WaitForKey;
 };
 
+// EFI block I/O protocol; on a partition handle LBA 0 is the partition start
+typedef struct
+{
+    UINT32 MediaId;
+    UINT8 RemovableMedia;
+    UINT8 MediaPresent;
+    UINT8 LogicalPartition;
+    UINT8 ReadOnly;
+    UINT8 WriteCaching;
+    UINT32 BlockSize;
+    UINT32 IoAlign;
+    UINT64 LastBlock;
+} EFI_BLOCK_IO_MEDIA;
+
+struct _EFI_BLOCK_IO_PROTOCOL
+{
+    UINT64 Revision;
+    EFI_BLOCK_IO_MEDIA *Media;
+    EFI_STATUS (*Reset)(EFI_BLOCK_IO_PROTOCOL *This, UINT8 ExtendedVerification);
+    EFI_STATUS (*ReadBlocks)(EFI_BLOCK_IO_PROTOCOL *This, UINT32 MediaId, UINT64 Lba, UINTN BufferSize, void *Buffer);
+    EFI_STATUS (*WriteBlocks)(EFI_BLOCK_IO_PROTOCOL *This, UINT32 MediaId, UINT64 Lba, UINTN BufferSize, void *Buffer);
+    EFI_STATUS (*FlushBlocks)(EFI_BLOCK_IO_PROTOCOL *This);
+};
+
+// EFI partition information protocol (UEFI 2.7)
+#define PARTITION_TYPE_GPT 0x02
+
+typedef struct
+{
+    EFI_GUID PartitionTypeGUID;
+    EFI_GUID UniquePartitionGUID;
+    UINT64 StartingLBA;
+    UINT64 EndingLBA;
+    UINT64 Attributes;
+    UINT16 PartitionName[36];
+} __attribute__((packed)) EFI_PARTITION_ENTRY;
+
+typedef struct
+{
+    UINT32 Revision;
+    UINT32 Type;
+    UINT8 System;
+    UINT8 Reserved[7];
+    union
+    {
+        UINT8 Mbr[16];
+        EFI_PARTITION_ENTRY Gpt;
+    } Info;
+} __attribute__((packed)) EFI_PARTITION_INFO_PROTOCOL;
+
 // Scan codes
 #define SCAN_UP 0x01
 #define SCAN_DOWN 0x02
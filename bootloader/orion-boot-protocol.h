@@ -50,6 +50,27 @@ extern "C"
 #define ORION_INFO_ACPI 0x0005
 #define ORION_INFO_EFI 0x0006
 
+// Memory map entry types, numbered as in Multiboot memory maps
+#define ORION_MEMORY_AVAILABLE 1
+#define ORION_MEMORY_RESERVED 2
+#define ORION_MEMORY_ACPI_RECLAIMABLE 3
+#define ORION_MEMORY_ACPI_NVS 4
+#define ORION_MEMORY_BAD 5
+#define ORION_MEMORY_BOOTLOADER_RECLAIMABLE 6 // Loader data still in use at entry
+#define ORION_MEMORY_KERNEL 7                 // Kernel image and boot modules
+#define ORION_MEMORY_FRAMEBUFFER 8
+
+#define ORION_MODULE_NAME_MAX 64
+
+// Boot control record (own GPT partition, written by the installer)
+#define ORION_BOOTCTL_MAGIC 0x5443424F // "OBCT" in ASCII
+#define ORION_BOOTCTL_VERSION 1
+#define ORION_BOOTCTL_COPY_STRIDE 4096 // Second copy 4 KiB into the partition
+#define ORION_BOOTCTL_KERNEL_MAX 64
+#define ORION_BOOTCTL_CMDLINE_MAX 256
+#define ORION_SLOT_BOOTABLE (1u << 0)   // Slot holds a complete system image
+#define ORION_SLOT_SUCCESSFUL (1u << 1) // A boot from the slot completed
+
     // ====================================
     // DATA STRUCTURES
     // ====================================
@@ -152,6 +173,79 @@ extern "C"
         char firmware_vendor[64];     // Firmware vendor string
     } __attribute__((packed));
 
+    /**
+     * Linear framebuffer set up by the bootloader (direct RGB colour only)
+     */
+    struct orion_framebuffer_info
+    {
+        struct orion_info_tag header; // type = ORION_INFO_FRAMEBUFFER
+        uint64_t address;             // Physical address of the first pixel
+        uint32_t width;               // Visible pixels per line
+        uint32_t height;              // Lines
+        uint32_t pitch;               // Bytes per line
+        uint16_t bpp;                 // Bits per pixel
+        uint8_t red_size;             // Colour channel widths and positions
+        uint8_t red_shift;
+        uint8_t green_size;
+        uint8_t green_shift;
+        uint8_t blue_size;
+        uint8_t blue_shift;
+    } __attribute__((packed));
+
+    /**
+     * ACPI root pointer
+     */
+    struct orion_acpi_info
+    {
+        struct orion_info_tag header; // type = ORION_INFO_ACPI
+        uint64_t rsdp;                // Physical address of the RSDP
+    } __attribute__((packed));
+
+    /**
+     * Boot modules (initial ramdisk, early drivers) loaded next to the kernel
+     */
+    struct orion_modules_info
+    {
+        struct orion_info_tag header; // type = ORION_INFO_MODULES
+        uint32_t module_count;        // Number of module entries
+        uint32_t reserved;            // Padding for alignment
+        // Followed by module entries
+    } __attribute__((packed));
+
+    struct orion_module_entry
+    {
+        uint64_t base;                    // Physical load address
+        uint64_t size;                    // Size in bytes
+        char name[ORION_MODULE_NAME_MAX]; // NUL terminated path or name
+    } __attribute__((packed));
+
+    struct orion_boot_slot
+    {
+        uint8_t guid[16];                         // Unique GUID of the system partition
+        uint32_t flags;                           // ORION_SLOT_*
+        uint32_t tries;                           // Boots left while not successful
+        char kernel[ORION_BOOTCTL_KERNEL_MAX];    // NUL terminated path in the slot
+        uint8_t reserved[8];
+    } __attribute__((packed));
+
+    /**
+     * Boot control record, kept twice in the boot control partition; the
+     * intact copy with the higher sequence wins. checksum is orion_checksum
+     * over every byte before it
+     */
+    struct orion_boot_control
+    {
+        uint32_t magic;   // ORION_BOOTCTL_MAGIC
+        uint32_t version; // ORION_BOOTCTL_VERSION
+        uint32_t sequence;
+        uint32_t active_slot;
+        struct orion_boot_slot slot[2];
+        uint8_t data_guid[16];                  // Unique GUID of the data partition
+        char cmdline[ORION_BOOTCTL_CMDLINE_MAX]; // Appended after root= and data=
+        uint8_t reserved[28];
+        uint32_t checksum;
+    } __attribute__((packed));
+
     // ====================================
     // UTILITY FUNCTIONS
     // ====================================
@@ -173,6 +267,63 @@ extern "C"
         return ~checksum; // One's complement
     }
 
+    /**
+     * Fill in both boot info checksums once all information structures are
+     * written. The header checksum covers the whole header with its own
+     * field zeroed, and so also covers data_checksum
+     */
+    static inline void orion_boot_info_seal(struct orion_boot_info *info)
+    {
+        info->data_checksum = orion_checksum((const uint8_t *)info + sizeof(struct orion_boot_info),
+                                             info->total_size - sizeof(struct orion_boot_info));
+        info->header_checksum = 0;
+        info->header_checksum = orion_checksum(info, sizeof(struct orion_boot_info));
+    }
+
+    /**
+     * Pick the system slot to start, as BootControl::select of
+     * lib/orion_install: a slot that has not completed a boot yet uses up
+     * one of its tries, and one without tries left is marked unbootable in
+     * favour of the other slot. Returns the slot, or -1 when neither can
+     * boot; *changed tells whether the record must be written back
+     */
+    static inline int orion_boot_control_select(struct orion_boot_control *control, int *changed)
+    {
+        uint32_t slot = control->active_slot > 1 ? 1 : control->active_slot;
+
+        *changed = 0;
+        for (int attempt = 0; attempt < 2; attempt++)
+        {
+            struct orion_boot_slot *entry = &control->slot[slot];
+            if (entry->flags & ORION_SLOT_BOOTABLE)
+            {
+                if (entry->flags & ORION_SLOT_SUCCESSFUL)
+                    return (int)slot;
+                if (entry->tries > 0)
+                {
+                    entry->tries--;
+                    *changed = 1;
+                    return (int)slot;
+                }
+                entry->flags &= ~ORION_SLOT_BOOTABLE;
+            }
+            slot = 1 - slot;
+            control->active_slot = slot;
+            *changed = 1;
+        }
+        return -1;
+    }
+
+    /**
+     * Bump the sequence and checksum a record before it is written back,
+     * over the copy at (sequence % 2) * ORION_BOOTCTL_COPY_STRIDE
+     */
+    static inline void orion_boot_control_seal(struct orion_boot_control *control)
+    {
+        control->sequence++;
+        control->checksum = orion_checksum(control, sizeof(*control) - sizeof(control->checksum));
+    }
+
     /**
      * Validate Orion boot header
      */
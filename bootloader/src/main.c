@@ -10,6 +10,10 @@
                      L"Copyright (c) 2024 Orion OS Project\r\n"            \
                      L"========================================\r\n\r\n"
 
+// Boot control partition written by the installer (lib/orion_install/src/gpt.rs)
+#define ORION_BOOT_CONTROL_TYPE_GUID \
+    {0xB1E8D7A4, 0x3C5F, 0x4E69, {0x9A, 0x2B, 0x7F, 0x0C, 0x1D, 0x8E, 0x6A, 0x35}}
+
 // Global UEFI variables
 EFI_HANDLE ImageHandle;
 EFI_SYSTEM_TABLE *SystemTable;
@@ -80,9 +84,7 @@ EFI_STATUS prepare_orion_boot_info(struct orion_boot_info **boot_info, UINTN *in
     efi_info->firmware_revision = SystemTable->FirmwareRevision;
 
     // Calculate checksums
-    info->header_checksum = orion_checksum(info, sizeof(struct orion_boot_info));
-    info->data_checksum = orion_checksum((UINT8 *)info + sizeof(struct orion_boot_info),
-                                         total_size - sizeof(struct orion_boot_info));
+    orion_boot_info_seal(info);
 
     *boot_info = info;
     *info_size = total_size;
@@ -96,6 +98,123 @@ EFI_STATUS prepare_orion_boot_info(struct orion_boot_info **boot_info, UINTN *in
     return EFI_SUCCESS;
 }
 
+static int guid_equal(const EFI_GUID *a, const EFI_GUID *b)
+{
+    const UINT8 *x = (const UINT8 *)a;
+    const UINT8 *y = (const UINT8 *)b;
+
+    for (UINTN i = 0; i < sizeof(EFI_GUID); i++)
+    {
+        if (x[i] != y[i])
+            return 0;
+    }
+    return 1;
+}
+
+// Block I/O of the boot control partition, if this disk was installed
+static EFI_STATUS find_boot_control(EFI_BLOCK_IO_PROTOCOL **BlockIo)
+{
+    EFI_GUID PartitionInfoGuid = EFI_PARTITION_INFO_PROTOCOL_GUID;
+    EFI_GUID BlockIoGuid = EFI_BLOCK_IO_PROTOCOL_GUID;
+    EFI_GUID ControlType = ORION_BOOT_CONTROL_TYPE_GUID;
+    EFI_HANDLE *Handles;
+    UINTN HandleCount;
+    EFI_STATUS Status;
+
+    Status = SystemTable->BootServices->LocateHandleBuffer(ByProtocol, &PartitionInfoGuid, NULL, &HandleCount,
+                                                           &Handles);
+    if (Status != EFI_SUCCESS)
+        return EFI_NOT_FOUND;
+
+    Status = EFI_NOT_FOUND;
+    for (UINTN i = 0; i < HandleCount; i++)
+    {
+        EFI_PARTITION_INFO_PROTOCOL *Info;
+        if (SystemTable->BootServices->HandleProtocol(Handles[i], &PartitionInfoGuid, (void **)&Info) != EFI_SUCCESS)
+            continue;
+        if (Info->Type != PARTITION_TYPE_GPT || !guid_equal(&Info->Info.Gpt.PartitionTypeGUID, &ControlType))
+            continue;
+        if (SystemTable->BootServices->HandleProtocol(Handles[i], &BlockIoGuid, (void **)BlockIo) == EFI_SUCCESS)
+        {
+            Status = EFI_SUCCESS;
+            break;
+        }
+    }
+
+    SystemTable->BootServices->FreePool(Handles);
+    return Status;
+}
+
+static int boot_control_valid(const struct orion_boot_control *control)
+{
+    return control->magic == ORION_BOOTCTL_MAGIC && control->version == ORION_BOOTCTL_VERSION &&
+           control->checksum == orion_checksum(control, sizeof(*control) - sizeof(control->checksum));
+}
+
+// Pick the system slot through the boot control record. An update that
+// has not completed a boot uses up one try here; once it has none left
+// the previous slot is started again. EFI_NOT_FOUND when the disk has no
+// boot control partition (live media)
+EFI_STATUS select_system_slot(struct orion_boot_control *control, int *slot)
+{
+    EFI_BLOCK_IO_PROTOCOL *BlockIo;
+    EFI_STATUS Status;
+    UINT8 *buffer;
+    UINTN size;
+    int found = 0;
+    int changed;
+
+    Status = find_boot_control(&BlockIo);
+    if (Status != EFI_SUCCESS)
+        return Status;
+
+    UINT32 block_size = BlockIo->Media->BlockSize;
+    size = block_size > sizeof(*control) ? block_size : sizeof(*control);
+    Status = SystemTable->BootServices->AllocatePool(EfiLoaderData, size, (VOID **)&buffer);
+    if (Status != EFI_SUCCESS)
+        return Status;
+
+    // Newest intact copy of the two
+    for (UINT32 copy = 0; copy < 2; copy++)
+    {
+        UINT64 lba = (UINT64)copy * ORION_BOOTCTL_COPY_STRIDE / block_size;
+        if (BlockIo->ReadBlocks(BlockIo, BlockIo->Media->MediaId, lba, size, buffer) != EFI_SUCCESS)
+            continue;
+        const struct orion_boot_control *record = (const struct orion_boot_control *)buffer;
+        if (boot_control_valid(record) && (!found || record->sequence > control->sequence))
+        {
+            *control = *record;
+            found = 1;
+        }
+    }
+
+    if (!found)
+    {
+        SystemTable->BootServices->FreePool(buffer);
+        return EFI_VOLUME_CORRUPTED;
+    }
+
+    *slot = orion_boot_control_select(control, &changed);
+    if (changed)
+    {
+        orion_boot_control_seal(control);
+        for (UINTN i = 0; i < size; i++)
+            buffer[i] = 0;
+        *(struct orion_boot_control *)buffer = *control;
+        UINT64 lba = (UINT64)(control->sequence % 2) * ORION_BOOTCTL_COPY_STRIDE / block_size;
+        if (BlockIo->WriteBlocks(BlockIo, BlockIo->Media->MediaId, lba, size, buffer) != EFI_SUCCESS ||
+            BlockIo->FlushBlocks(BlockIo) != EFI_SUCCESS)
+        {
+            // The try is not counted: an update that hangs is started
+            // again until the record can be written
+            Print(L"Warning: Cannot update the boot control record\r\n");
+        }
+    }
+
+    SystemTable->BootServices->FreePool(buffer);
+    return *slot < 0 ? EFI_LOAD_ERROR : EFI_SUCCESS;
+}
+
 // Kernel loading function (enhanced with Orion Protocol)
 EFI_STATUS load_kernel(EFI_PHYSICAL_ADDRESS *KernelBase, UINTN *KernelSize)
 {
@@ -215,6 +334,21 @@ EFI_STATUS EFIAPI efi_main(EFI_HANDLE handle, EFI_SYSTEM_TABLE *st)
     case 1: // Start Orion Kernel
         Print(L"\r\n=== LOADING ORION KERNEL ===\r\n");
 
+        // Installed disks boot from one of two system slots
+        struct orion_boot_control control;
+        int slot;
+        Status = select_system_slot(&control, &slot);
+        if (Status == EFI_SUCCESS)
+        {
+            Print(L"System slot %c (%a), %d tries left\r\n", slot == 0 ? 'A' : 'B', control.slot[slot].kernel,
+                  control.slot[slot].tries);
+        }
+        else if (Status != EFI_NOT_FOUND)
+        {
+            Print(L"Error: No bootable system slot\r\n");
+            return Status;
+        }
+
         // Load kernel
         Status = load_kernel(&KernelBase, &KernelSize);
         if (EFI_ERROR(Status))
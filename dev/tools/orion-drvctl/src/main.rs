@@ -0,0 +1,314 @@
+/*
+ * Orion Operating System - Driver Control Tool
+ *
+ * Front end of the I/O server for drivers loaded at run time:
+ *
+ *   orion-drvctl list
+ *   orion-drvctl load <image> <manifest> [name]
+ *   orion-drvctl unload [--force] <name>
+ *   orion-drvctl holds
+ *
+ * `list` shows the devices with the driver bound to each, then the loads
+ * still waiting for what they require. `load` installs a signed driver
+ * image with its manifest, whose `match` lines name the devices it
+ * drives, and binds it to those no driver claims; the name defaults to
+ * the image file name. `unload` stops a driver on every device it drives
+ * and forgets it; it is refused while one of those devices is held by a
+ * mount or a socket, listing the holds, unless forced: the holders are
+ * then told the device is gone and fail what they had outstanding.
+ * `holds` lists who holds which device and why.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+use orion_sys::{close, open, read, write, O_RDONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// I/O server requests (see services/io/src/protocol.rs)
+const OP_LIST_DEVICES: u32 = 8;
+const OP_LIST_DEFERRED: u32 = 12;
+const OP_INSTALL_DRIVER: u32 = 13;
+const OP_UNLOAD_DRIVER: u32 = 14;
+const OP_LIST_HOLDS: u32 = 17;
+const UNLOAD_FORCE: u32 = 1 << 0;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EIO: i32 = -5;
+const STATUS_EBUSY: i32 = -16;
+const STATUS_EEXIST: i32 = -17;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOSPC: i32 = -28;
+const STATUS_EKEYREJECTED: i32 = -129;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-drvctl list
+       orion-drvctl load <image> <manifest> [name]
+       orion-drvctl unload [--force] <name>
+       orion-drvctl holds
+";
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+/// Send a request to `channel`; the reply payload comes back with the
+/// status, failed requests carrying details too
+fn call(channel: &mut IpcChannel, request: &[u8]) -> (i32, Vec<u8>) {
+    match channel.call(request) {
+        Ok(response) if response.len() >= 4 => {
+            (i32::from_le_bytes([response[0], response[1], response[2], response[3]]), response[4..].to_vec())
+        }
+        _ => (STATUS_ENOENT, Vec::new()),
+    }
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EPERM => String::from("permission denied"),
+        STATUS_ENOENT => String::from("no such driver, or no I/O server"),
+        STATUS_EIO => String::from("the driver failed to start"),
+        STATUS_EBUSY => String::from("devices are in use"),
+        STATUS_EEXIST => String::from("a driver of that name is installed already"),
+        STATUS_EINVAL => String::from("invalid manifest, it needs at least one match line"),
+        STATUS_ENOSPC => String::from("too many drivers"),
+        STATUS_EKEYREJECTED => String::from("the image signature is not trusted"),
+        status => format!("error {}", status),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8).map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Decode a `len, utf-8 bytes` field, returning it with the offset after it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset) as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from_utf8_lossy(bytes).into_owned(), offset + 4 + len))
+}
+
+fn with_string(request: &mut Vec<u8>, text: &str) {
+    request.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    request.extend_from_slice(text.as_bytes());
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let fd = open(path, O_RDONLY).map_err(|status| format!("{}: error {}", path, status))?;
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Ok(data),
+            Ok(count) => data.extend_from_slice(&chunk[..count]),
+            Err(status) => break Err(format!("{}: error {}", path, status)),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+fn list(channel: &mut IpcChannel) -> Result<(), String> {
+    let (status, records) = call(channel, &OP_LIST_DEVICES.to_le_bytes());
+    if status != STATUS_OK {
+        return Err(describe(status));
+    }
+    print(STDOUT, &format!("{:<18}  {:<9}  {:>6}  DRIVER\n", "DEVICE", "PCI", "PID"));
+    let mut offset = 0;
+    while offset + 20 <= records.len() {
+        let handle = read_u64(&records, offset);
+        let (vendor, device) = (read_u32(&records, offset + 8) & 0xffff, read_u32(&records, offset + 8) >> 16);
+        let pid = read_u64(&records, offset + 12);
+        let Some((driver, next)) = read_string(&records, offset + 20) else {
+            break;
+        };
+        let pid = if pid == 0 { String::from("-") } else { format!("{}", pid) };
+        let driver = if driver.is_empty() { String::from("-") } else { driver };
+        print(STDOUT, &format!("{:#018x}  {:04x}:{:04x}  {:>6}  {}\n", handle, vendor, device, pid, driver));
+        offset = next;
+    }
+
+    let (status, records) = call(channel, &OP_LIST_DEFERRED.to_le_bytes());
+    if status != STATUS_OK {
+        return Err(describe(status));
+    }
+    if read_u32(&records, 0) == 0 {
+        return Ok(());
+    }
+    print(STDOUT, &format!("\n{:<18}  {:<16}  WAITING FOR\n", "DEVICE", "DRIVER"));
+    let mut offset = 4;
+    while offset + 8 <= records.len() {
+        let handle = read_u64(&records, offset);
+        let Some((driver, next)) = read_string(&records, offset + 8) else {
+            break;
+        };
+        let count = read_u32(&records, next);
+        let mut missing = Vec::new();
+        offset = next + 4;
+        for _ in 0..count {
+            let Some((facility, next)) = read_string(&records, offset) else {
+                break;
+            };
+            missing.push(facility);
+            offset = next;
+        }
+        print(STDOUT, &format!("{:#018x}  {:<16}  {}\n", handle, driver, missing.join(", ")));
+    }
+    Ok(())
+}
+
+/// Print LIST_HOLDS records
+fn print_holds(records: &[u8]) {
+    print(STDOUT, &format!("{:>6}  {:<18}  {:>6}  PURPOSE\n", "HOLD", "DEVICE", "HOLDER"));
+    let mut offset = 0;
+    while offset + 20 <= records.len() {
+        let (id, device, holder) =
+            (read_u32(records, offset), read_u64(records, offset + 4), read_u64(records, offset + 12));
+        let Some((purpose, next)) = read_string(records, offset + 20) else {
+            break;
+        };
+        print(STDOUT, &format!("{:>6}  {:#018x}  {:>6}  {}\n", id, device, holder, purpose));
+        offset = next;
+    }
+}
+
+fn holds(channel: &mut IpcChannel) -> Result<(), String> {
+    let (status, records) = call(channel, &OP_LIST_HOLDS.to_le_bytes());
+    if status != STATUS_OK {
+        return Err(describe(status));
+    }
+    print_holds(&records);
+    Ok(())
+}
+
+fn load(channel: &mut IpcChannel, image_path: &str, manifest_path: &str, name: Option<&str>) -> Result<(), String> {
+    let image = read_file(image_path)?;
+    let manifest = read_file(manifest_path)?;
+    let manifest = core::str::from_utf8(&manifest).map_err(|_| format!("{}: not UTF-8 text", manifest_path))?;
+    let name = name.unwrap_or_else(|| {
+        let file = image_path.rsplit('/').next().unwrap_or(image_path);
+        file.split('.').next().unwrap_or(file)
+    });
+
+    let mut request = OP_INSTALL_DRIVER.to_le_bytes().to_vec();
+    with_string(&mut request, name);
+    with_string(&mut request, manifest);
+    request.extend_from_slice(&image);
+    let (status, bound) = call(channel, &request);
+    if status != STATUS_OK {
+        return Err(format!("{}: {}", name, describe(status)));
+    }
+
+    let count = read_u32(&bound, 0);
+    print(STDOUT, &format!("{} installed, bound to {} devices\n", name, count));
+    for index in 0..count as usize {
+        let (handle, pid) = (read_u64(&bound, 4 + index * 16), read_u64(&bound, 12 + index * 16));
+        if pid == 0 {
+            print(STDOUT, &format!("  {:#018x}  deferred, see orion-drvctl list\n", handle));
+        } else {
+            print(STDOUT, &format!("  {:#018x}  pid {}\n", handle, pid));
+        }
+    }
+    Ok(())
+}
+
+fn unload(channel: &mut IpcChannel, name: &str, force: bool) -> Result<(), String> {
+    let mut request = OP_UNLOAD_DRIVER.to_le_bytes().to_vec();
+    with_string(&mut request, name);
+    request.extend_from_slice(&(if force { UNLOAD_FORCE } else { 0 }).to_le_bytes());
+    match call(channel, &request) {
+        (STATUS_OK, stopped) => {
+            print(STDOUT, &format!("{} unloaded from {} devices\n", name, read_u32(&stopped, 0)));
+            Ok(())
+        }
+        (STATUS_EBUSY, held) => {
+            print_holds(&held);
+            Err(format!("{}: devices are in use, release them or unload with --force", name))
+        }
+        (status, _) => Err(format!("{}: {}", name, describe(status))),
+    }
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let mut channel = IpcChannel::connect("io");
+    let result = match args {
+        [_, "list"] => list(&mut channel),
+        [_, "load", image, manifest] => load(&mut channel, image, manifest, None),
+        [_, "load", image, manifest, name] => load(&mut channel, image, manifest, Some(name)),
+        [_, "unload", name] => unload(&mut channel, name, false),
+        [_, "unload", "--force", name] => unload(&mut channel, name, true),
+        [_, "holds"] => holds(&mut channel),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(message) => {
+            print(STDERR, &format!("orion-drvctl: {}\n", message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,281 @@
+/*
+ * Orion Operating System - Fetch Tool
+ *
+ * Downloads a file over HTTP or HTTPS:
+ *
+ *   orion-fetch [-c] [-o file] [--ca handle] [--checksum algo:hex] <url>
+ *
+ * The body goes to `file`, or to the last path segment of the URL when
+ * no file is given. `-c` continues an interrupted download after what
+ * the file already holds; without it the file is replaced. https servers
+ * are checked against the CA certificate under keyring handle `handle`.
+ * `--checksum` takes `sha256:<hex>` or `sha512:<hex>`; a file that does
+ * not match is reported and kept so the download can be inspected.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_fetch::{Checksum, Client, FetchConfig, FetchError, NetConnector, Sink, Url};
+use orion_http::socket::NetChannel;
+use orion_ipc::IpcChannel;
+use orion_sys::{close, nanosleep, open, read, write, O_APPEND, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+/// Pause between two polls of an idle connection
+const POLL_INTERVAL_NS: u64 = 1_000_000;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-fetch [-c] [-o file] [--ca handle] [--checksum algo:hex] <url>
+";
+
+/// IPC channel to the network server, one per connection
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn poll_wait() {
+    let _ = nanosleep(POLL_INTERVAL_NS);
+}
+
+fn describe(error: FetchError) -> String {
+    let text = match error {
+        FetchError::InvalidUrl => "invalid URL",
+        FetchError::UnsupportedScheme => "only http and https URLs are supported",
+        FetchError::UnresolvedHost => "host must be an IPv4 address",
+        FetchError::NoTrustAnchor => "https needs a CA certificate (--ca)",
+        FetchError::Connect => "connection refused",
+        FetchError::Closed => "connection closed",
+        FetchError::Timeout => "timed out",
+        FetchError::Malformed => "malformed response",
+        FetchError::Status(status) => return format!("server answered {}", status),
+        FetchError::RangeIgnored => "server ignored the range",
+        FetchError::TooManyRedirects => "too many redirects",
+        FetchError::InsecureRedirect => "refused redirect from https to http",
+        FetchError::ChecksumMismatch => "checksum mismatch",
+        FetchError::Sink => "cannot write the output file",
+    };
+    String::from(text)
+}
+
+/// Output file; `stored` follows what was written so it never needs a seek
+struct FileSink {
+    path: String,
+    fd: u64,
+    stored: u64,
+}
+
+impl FileSink {
+    /// Open `path`, keeping its contents when `resume` is set
+    fn open(path: &str, resume: bool) -> Option<FileSink> {
+        let stored = if resume { FileSink::scan(path, &mut |_| ()).unwrap_or(0) } else { 0 };
+        let flags = if resume { O_WRONLY | O_CREAT | O_APPEND } else { O_WRONLY | O_CREAT | O_TRUNC };
+        let fd = open(path, flags).ok()?;
+        Some(FileSink { path: String::from(path), fd, stored })
+    }
+
+    /// Read the file from the start, returning its length
+    fn scan(path: &str, consumer: &mut dyn FnMut(&[u8])) -> Option<u64> {
+        let fd = open(path, O_RDONLY).ok()?;
+        let mut chunk = [0u8; 4096];
+        let mut length = 0;
+        let result = loop {
+            match read(fd, &mut chunk) {
+                Ok(0) => break Some(length),
+                Ok(count) => {
+                    consumer(&chunk[..count]);
+                    length += count as u64;
+                }
+                Err(_) => break None,
+            }
+        };
+        let _ = close(fd);
+        result
+    }
+}
+
+impl Sink for FileSink {
+    fn stored(&self) -> u64 {
+        self.stored
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> Result<(), FetchError> {
+        while !data.is_empty() {
+            match write(self.fd, data) {
+                Ok(count) if count > 0 => {
+                    data = &data[count..];
+                    self.stored += count as u64;
+                }
+                _ => return Err(FetchError::Sink),
+            }
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), FetchError> {
+        let _ = close(self.fd);
+        self.fd = open(&self.path, O_WRONLY | O_CREAT | O_TRUNC).map_err(|_| FetchError::Sink)?;
+        self.stored = 0;
+        Ok(())
+    }
+
+    fn replay(&mut self, consumer: &mut dyn FnMut(&[u8])) -> Result<(), FetchError> {
+        match FileSink::scan(&self.path, consumer) {
+            Some(length) if length == self.stored => Ok(()),
+            _ => Err(FetchError::Sink),
+        }
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        let _ = close(self.fd);
+    }
+}
+
+struct Options<'a> {
+    resume: bool,
+    output: Option<&'a str>,
+    trust_anchor: Option<u64>,
+    checksum: Option<Checksum>,
+    url: &'a str,
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let mut options = Options { resume: false, output: None, trust_anchor: None, checksum: None, url: "" };
+    let mut index = 1;
+    while index < args.len() {
+        match args[index] {
+            "-c" => options.resume = true,
+            "-o" => {
+                index += 1;
+                options.output = Some(*args.get(index)?);
+            }
+            "--ca" => {
+                index += 1;
+                options.trust_anchor = Some(args.get(index)?.parse().ok()?);
+            }
+            "--checksum" => {
+                index += 1;
+                options.checksum = Some(Checksum::parse(args.get(index)?)?);
+            }
+            url if options.url.is_empty() && !url.starts_with('-') => options.url = url,
+            _ => return None,
+        }
+        index += 1;
+    }
+    (!options.url.is_empty()).then_some(options)
+}
+
+/// Last path segment of the URL, without the query
+fn default_output(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let path = url.path.split('?').next().unwrap_or("");
+    let name = path.rsplit('/').next().unwrap_or("");
+    (!name.is_empty() && name != "." && name != "..").then(|| String::from(name))
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let options = match parse_options(args) {
+        Some(options) => options,
+        None => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let output = match options.output.map(String::from).or_else(|| default_output(options.url)) {
+        Some(output) => output,
+        None => {
+            print(STDERR, "orion-fetch: cannot name the output file, use -o\n");
+            return EXIT_USAGE;
+        }
+    };
+    let mut sink = match FileSink::open(&output, options.resume) {
+        Some(sink) => sink,
+        None => {
+            print(STDERR, &format!("orion-fetch: {}: cannot open\n", output));
+            return EXIT_FAILURE;
+        }
+    };
+
+    let mut connector = NetConnector::new(|| Some(NetIpc(IpcChannel::connect("net"))), poll_wait);
+    if let Some(handle) = options.trust_anchor {
+        connector = connector.with_trust_anchor(handle);
+    }
+    let mut client = Client::new(connector, FetchConfig::default());
+
+    match client.download(options.url, &mut sink, options.checksum.as_ref()) {
+        Ok(fetched) => {
+            if fetched.resumed_from > 0 {
+                print(STDOUT, &format!("resumed after {} bytes\n", fetched.resumed_from));
+            }
+            print(STDOUT, &format!("{}: {} bytes from {}\n", output, fetched.length, fetched.url));
+            EXIT_OK
+        }
+        Err(error) => {
+            print(STDERR, &format!("orion-fetch: {}: {}\n", options.url, describe(error)));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,237 @@
+/*
+ * Orion Operating System - Firmware Update Tool
+ *
+ * Updates device firmware through the driver that owns the device:
+ *
+ *   orion-fwupdate status   <driver>
+ *   orion-fwupdate update   <driver> <image> [confirm-seconds]
+ *   orion-fwupdate confirm  <driver>
+ *   orion-fwupdate rollback <driver>
+ *   orion-fwupdate abort    <driver>
+ *   orion-fwupdate dump     <driver> <file>
+ *
+ * `driver` is the name of the driver's IPC channel (for example
+ * "nvme-ultra-modern"). `update` sends the signed image, which the driver
+ * verifies before anything is written, and activates it; the new firmware
+ * must then be confirmed within the given time (60 seconds by default)
+ * or the driver goes back to the previous image on its own. `dump` saves
+ * the firmware the device runs (the whole NVM of an e1000e) to a file,
+ * to keep a copy before an update.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_fwupdate::{FirmwareClient, FirmwareStatus, FwError, Transport, UpdateState};
+use orion_ipc::IpcChannel;
+use orion_sys::{close, open, read, write, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+/// Image bytes carried by one STAGE request
+const STAGE_CHUNK: usize = 16 * 1024;
+const DEFAULT_CONFIRM_SECONDS: u32 = 60;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-fwupdate status   <driver>
+       orion-fwupdate update   <driver> <image> [confirm-seconds]
+       orion-fwupdate confirm  <driver>
+       orion-fwupdate rollback <driver>
+       orion-fwupdate abort    <driver>
+       orion-fwupdate dump     <driver> <file>
+";
+
+/// Channel of the driver that owns the device
+struct DriverIpc(IpcChannel);
+
+impl Transport for DriverIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn describe(error: FwError) -> &'static str {
+    match error {
+        FwError::InvalidArgument => "invalid argument",
+        FwError::Busy => "another update is in progress",
+        FwError::NotFound => "no update to act on",
+        FwError::TooLarge => "image too large for the device",
+        FwError::Malformed => "malformed image",
+        FwError::Rejected => "image signature rejected",
+        FwError::WrongDevice => "image built for another device",
+        FwError::Locked => "operation not permitted",
+        FwError::NoRollback => "the device cannot roll back",
+        FwError::Unsupported => "firmware update not supported by the driver",
+        FwError::Device => "device error",
+    }
+}
+
+/// Version field as text, without its NUL padding
+fn version_text(version: &[u8]) -> String {
+    let end = version.iter().position(|&byte| byte == 0).unwrap_or(version.len());
+    String::from_utf8_lossy(&version[..end]).into_owned()
+}
+
+fn state_text(state: UpdateState) -> &'static str {
+    match state {
+        UpdateState::Idle => "idle",
+        UpdateState::Receiving => "receiving image",
+        UpdateState::Verified => "image verified",
+        UpdateState::Activated => "activated, waiting for confirmation",
+    }
+}
+
+fn print_status(status: &FirmwareStatus) {
+    print(STDOUT, &format!("version:      {}\n", version_text(&status.version)));
+    print(STDOUT, &format!("active slot:  {}\n", status.active_slot));
+    print(STDOUT, &format!("staging slot: {}\n", status.staging_slot));
+    print(STDOUT, &format!("rollback:     {}\n", if status.can_rollback { "yes" } else { "no" }));
+    print(STDOUT, &format!("max size:     {} bytes\n", status.max_size));
+    print(STDOUT, &format!("update:       {}\n", state_text(status.state)));
+    if status.state == UpdateState::Receiving {
+        print(STDOUT, &format!("received:     {} bytes\n", status.received));
+    }
+}
+
+fn read_file(path: &str) -> Option<Vec<u8>> {
+    let fd = open(path, O_RDONLY).ok()?;
+    let mut image = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Some(image),
+            Ok(count) => image.extend_from_slice(&chunk[..count]),
+            Err(_) => break None,
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+fn update(client: &mut FirmwareClient<DriverIpc>, path: &str, confirm_seconds: u32) -> Result<(), FwError> {
+    let image = read_file(path).ok_or(FwError::InvalidArgument)?;
+    let version = client.stage(&image, STAGE_CHUNK)?;
+    print(STDOUT, &format!("verified firmware {}\n", version_text(&version)));
+
+    let slot = client.activate(confirm_seconds.saturating_mul(1000))?;
+    print(STDOUT, &format!("running from slot {}\n", slot));
+    print(STDOUT, &format!("check the device, then run `orion-fwupdate confirm` within {} seconds\n", confirm_seconds));
+    Ok(())
+}
+
+fn write_file(path: &str, data: &[u8]) -> Option<()> {
+    let fd = open(path, O_WRONLY | O_CREAT | O_TRUNC).ok()?;
+    let mut written = 0;
+    let result = loop {
+        if written == data.len() {
+            break Some(());
+        }
+        match write(fd, &data[written..]) {
+            Ok(0) | Err(_) => break None,
+            Ok(count) => written += count,
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+fn dump(client: &mut FirmwareClient<DriverIpc>, path: &str) -> Result<(), FwError> {
+    // The device reports its image size as the largest it takes
+    let limit = client.query()?.max_size;
+    let image = client.read_image(limit)?;
+    write_file(path, &image).ok_or(FwError::InvalidArgument)?;
+    print(STDOUT, &format!("saved {} bytes to {}\n", image.len(), path));
+    Ok(())
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let (command, driver) = match args {
+        [_, command, driver, ..] => (*command, *driver),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let mut client = FirmwareClient::new(DriverIpc(IpcChannel::connect(driver)));
+
+    let result = match (command, &args[3..]) {
+        ("status", []) => client.query().map(|status| print_status(&status)),
+        ("update", [path]) => update(&mut client, path, DEFAULT_CONFIRM_SECONDS),
+        ("update", [path, seconds]) => match seconds.parse() {
+            Ok(seconds) => update(&mut client, path, seconds),
+            Err(_) => Err(FwError::InvalidArgument),
+        },
+        ("confirm", []) => client.confirm(),
+        ("rollback", []) => client.rollback(),
+        ("abort", []) => client.abort(),
+        ("dump", [path]) => dump(&mut client, path),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(error) => {
+            print(STDERR, &format!("orion-fwupdate: {}: {}\n", driver, describe(error)));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,494 @@
+/*
+ * Orion Operating System - Installer Tool
+ *
+ * Installs Orion from the live system onto a whole disk:
+ *
+ *   orion-install list
+ *   orion-install plan    <disk>
+ *   orion-install install <disk> [--force] [--config file] [--cmdline text]
+ *
+ * `disk` is the name of the block driver's IPC channel, as shown by
+ * `list`, which asks the I/O server for the bound devices and keeps those
+ * whose driver answers the raw disk requests. `plan` shows the partitions
+ * an installation would create. `install` partitions the disk, copies the
+ * system partition and system images of the live medium, formats the
+ * data partition with the configuration from `file` (checked by the
+ * configuration server first; an empty configuration otherwise) and
+ * writes the boot control record. A disk that already holds a partition
+ * table is only overwritten with `--force`.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_blkio::control::BLK_INFO_READ_ONLY;
+use orion_blkio::{DiskClient, DiskInfo, Transport};
+use orion_install::config::EMPTY_DOCUMENT;
+use orion_install::installer::{BOOT_CONTROL, DATA, SYSTEM_A};
+use orion_install::{plan, Disk, Images, InstallError, Installer, Layout, PartitionTable, Source, Stage};
+use orion_ipc::IpcChannel;
+use orion_sys::{clock_get, close, open, read, write, O_RDONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+/// Images on the live medium
+const ESP_IMAGE: &str = "/live/esp.img";
+const SYSTEM_IMAGE: &str = "/live/system.img";
+
+const CLOCK_ID_REALTIME: u32 = 1;
+const STATUS_OK: i32 = 0;
+const STATUS_EINVAL: i32 = -22;
+
+const IO_OP_LIST_DEVICES: u32 = 8;
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+const CONFIG_OP_VALIDATE: u32 = 2;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-install list
+       orion-install plan    <disk>
+       orion-install install <disk> [--force] [--config file] [--cmdline text]
+";
+
+/// Channel of the driver that owns the disk
+struct DriverIpc(IpcChannel);
+
+impl Transport for DriverIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Whole disk through the raw disk requests of its driver
+struct BlockDisk {
+    client: DiskClient<DriverIpc>,
+    info: DiskInfo,
+}
+
+impl BlockDisk {
+    fn open(driver: &str) -> Result<Self, i32> {
+        let mut client = DiskClient::new(DriverIpc(IpcChannel::connect(driver)));
+        let info = client.info()?;
+        Ok(Self { client, info })
+    }
+}
+
+impl Disk for BlockDisk {
+    fn block_size(&self) -> u32 {
+        self.info.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.info.blocks
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        self.client.read(lba, buffer)
+    }
+
+    fn write(&mut self, lba: u64, data: &[u8]) -> Result<(), i32> {
+        self.client.write(lba, data)
+    }
+
+    fn flush(&mut self) -> Result<(), i32> {
+        self.client.flush()
+    }
+}
+
+/// Image file read from start to end; without a way to ask a file's size
+/// it is read through once when opened
+struct FileSource {
+    fd: u64,
+    position: u64,
+    size: u64,
+}
+
+impl FileSource {
+    fn open(path: &'static str) -> Result<Self, i32> {
+        let fd = open(path, O_RDONLY)?;
+        let mut chunk = [0u8; 4096];
+        let mut size = 0u64;
+        let counted = loop {
+            match read(fd, &mut chunk) {
+                Ok(0) => break Ok(size),
+                Ok(count) => size += count as u64,
+                Err(status) => break Err(status),
+            }
+        };
+        let _ = close(fd);
+        let size = counted?;
+        Ok(Self { fd: open(path, O_RDONLY)?, position: 0, size })
+    }
+}
+
+impl Source for FileSource {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<usize, i32> {
+        // The installer copies front to back
+        if offset != self.position {
+            return Err(STATUS_EINVAL);
+        }
+        let count = read(self.fd, buffer)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Drop for FileSource {
+    fn drop(&mut self) {
+        let _ = close(self.fd);
+    }
+}
+
+/// GUIDs and the file system UUID from the entropy service; `plan` cannot
+/// fail halfway, so a failed draw is remembered and checked afterwards
+struct Entropy {
+    channel: IpcChannel,
+    failed: bool,
+}
+
+impl Entropy {
+    fn draw(&mut self) -> [u8; 16] {
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+        request.extend_from_slice(&16u32.to_le_bytes());
+        request.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut bytes = [0u8; 16];
+        match self.channel.call(&request) {
+            Ok(response) if response.len() >= 20 && response[..4] == STATUS_OK.to_le_bytes() => {
+                bytes.copy_from_slice(&response[4..20]);
+            }
+            _ => self.failed = true,
+        }
+        bytes
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn size_text(bytes: u64) -> String {
+    const GIB: u64 = 1 << 30;
+    const MIB: u64 = 1 << 20;
+    if bytes >= 10 * GIB {
+        format!("{} GiB", bytes / GIB)
+    } else {
+        format!("{} MiB", bytes / MIB)
+    }
+}
+
+fn describe(error: InstallError) -> String {
+    match error {
+        InstallError::Disk(status) => format!("disk error {}", status),
+        InstallError::Source(status) => format!("reading the live images failed with error {}", status),
+        InstallError::TooSmall { needed, available } => {
+            format!("disk too small: {} needed, {} available", size_text(needed), size_text(available))
+        }
+        InstallError::BlockSize(size) => format!("unsupported block size {}", size),
+        InstallError::ReadOnly => String::from("the disk is read-only"),
+        InstallError::Verify(offset) => format!("data read back differs from what was written at byte {}", offset),
+        InstallError::FileTooLarge => String::from("the configuration does not fit the data file system"),
+        InstallError::NotFound => String::from("no partition table"),
+    }
+}
+
+fn read_file(path: &str) -> Option<Vec<u8>> {
+    let fd = open(path, O_RDONLY).ok()?;
+    let mut contents = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Some(contents),
+            Ok(count) => contents.extend_from_slice(&chunk[..count]),
+            Err(_) => break None,
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+/// Have the configuration server check a document, returning its message
+/// when the document is refused
+fn validate_config(document: &[u8]) -> Result<(), String> {
+    let mut request = CONFIG_OP_VALIDATE.to_le_bytes().to_vec();
+    request.extend_from_slice(&(document.len() as u32).to_le_bytes());
+    request.extend_from_slice(document);
+
+    let response = IpcChannel::connect("config")
+        .call(&request)
+        .map_err(|_| String::from("configuration server unavailable"))?;
+    if response.len() < 4 {
+        return Err(String::from("configuration server unavailable"));
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(()),
+        status => {
+            let message = response
+                .get(8..)
+                .map(|text| String::from_utf8_lossy(text).into_owned())
+                .unwrap_or_else(|| format!("error {}", status));
+            Err(message)
+        }
+    }
+}
+
+/// Driver channels of the bound devices, in the I/O server's order
+fn bound_drivers() -> Option<Vec<String>> {
+    let response = IpcChannel::connect("io").call(&IO_OP_LIST_DEVICES.to_le_bytes()).ok()?;
+    if response.len() < 4 || response[..4] != STATUS_OK.to_le_bytes() {
+        return None;
+    }
+    let mut drivers: Vec<String> = Vec::new();
+    let mut rest = &response[4..];
+    // handle, vendor, device, driver pid, then the driver name
+    while rest.len() >= 24 {
+        let length = u32::from_le_bytes(rest[20..24].try_into().ok()?) as usize;
+        let name = core::str::from_utf8(rest.get(24..24 + length)?).ok()?;
+        if !name.is_empty() && !drivers.iter().any(|driver| driver == name) {
+            drivers.push(String::from(name));
+        }
+        rest = &rest[24 + length..];
+    }
+    Some(drivers)
+}
+
+fn list() -> i32 {
+    let Some(drivers) = bound_drivers() else {
+        print(STDERR, "orion-install: the I/O server does not answer\n");
+        return EXIT_FAILURE;
+    };
+    for driver in drivers {
+        // Drivers of other device classes do not answer the disk requests
+        if let Ok(disk) = BlockDisk::open(&driver) {
+            let info = disk.info;
+            let read_only = if info.flags & BLK_INFO_READ_ONLY != 0 { "  read-only" } else { "" };
+            print(
+                STDOUT,
+                &format!("{:<24} {:>10}  {}-byte blocks{}\n", driver, size_text(info.size()), info.block_size, read_only),
+            );
+        }
+    }
+    EXIT_OK
+}
+
+fn open_images() -> Result<(FileSource, FileSource), String> {
+    let esp = FileSource::open(ESP_IMAGE).map_err(|status| format!("{}: error {}", ESP_IMAGE, status))?;
+    let system = FileSource::open(SYSTEM_IMAGE).map_err(|status| format!("{}: error {}", SYSTEM_IMAGE, status))?;
+    Ok((esp, system))
+}
+
+fn make_layout(disk: &BlockDisk, esp: &FileSource, system: &FileSource) -> Result<Layout, String> {
+    let mut entropy = Entropy { channel: IpcChannel::connect("entropy"), failed: false };
+    let layout = plan(disk.block_size(), disk.blocks(), esp.size(), system.size(), &mut || entropy.draw())
+        .map_err(describe)?;
+    if entropy.failed {
+        return Err(String::from("the entropy service does not answer"));
+    }
+    Ok(layout)
+}
+
+fn print_layout(layout: &Layout) {
+    for (index, partition) in layout.table.partitions.iter().enumerate() {
+        let (_, size) = layout.extent(index);
+        print(
+            STDOUT,
+            &format!(
+                "{}  {:<20} {:>10}  blocks {}-{}  {}\n",
+                index + 1,
+                partition.name,
+                size_text(size),
+                partition.first_lba,
+                partition.last_lba,
+                partition.guid
+            ),
+        );
+    }
+}
+
+struct Options<'a> {
+    force: bool,
+    config: Option<&'a str>,
+    cmdline: &'a str,
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let mut options = Options { force: false, config: None, cmdline: "" };
+    let mut index = 0;
+    while index < args.len() {
+        match args[index] {
+            "--force" => options.force = true,
+            "--config" => {
+                index += 1;
+                options.config = Some(args.get(index)?);
+            }
+            "--cmdline" => {
+                index += 1;
+                options.cmdline = args.get(index)?;
+            }
+            _ => return None,
+        }
+        index += 1;
+    }
+    Some(options)
+}
+
+/// Percentage of the stage, printed each time it moves
+struct Progress {
+    stage: Option<Stage>,
+    percent: u64,
+}
+
+impl Progress {
+    fn report(&mut self, stage: Stage, done: u64, total: u64) {
+        let percent = (done * 100).checked_div(total).unwrap_or(100);
+        if self.stage == Some(stage) && self.percent == percent {
+            return;
+        }
+        if self.stage.is_some() && self.stage != Some(stage) {
+            print(STDOUT, "\n");
+        }
+        self.stage = Some(stage);
+        self.percent = percent;
+        let name = match stage {
+            Stage::Partition => "partitioning",
+            Stage::Bootloader => "copying the system partition",
+            Stage::System => "copying the system image",
+            Stage::Verify => "verifying",
+            Stage::Data => "creating the data file system",
+            Stage::BootControl => "writing the boot control record",
+        };
+        print(STDOUT, &format!("\r{:<34} {:>3}%", name, percent));
+    }
+}
+
+fn install(driver: &str, options: &Options) -> Result<(), String> {
+    let mut disk = BlockDisk::open(driver).map_err(|status| format!("not a disk (error {})", status))?;
+    if disk.info.flags & BLK_INFO_READ_ONLY != 0 {
+        return Err(describe(InstallError::ReadOnly));
+    }
+    if !options.force && PartitionTable::read(&mut disk).is_ok() {
+        return Err(String::from("the disk is partitioned already, use --force to erase it"));
+    }
+
+    let document = match options.config {
+        Some(path) => read_file(path).ok_or_else(|| format!("{}: cannot read", path))?,
+        None => EMPTY_DOCUMENT.as_bytes().to_vec(),
+    };
+    validate_config(&document)?;
+
+    let (mut esp, mut system) = open_images()?;
+    let layout = make_layout(&disk, &esp, &system)?;
+
+    let mut progress = Progress { stage: None, percent: 0 };
+    let mut report = |stage: Stage, done: u64, total: u64| progress.report(stage, done, total);
+    let mut images = Images { esp: &mut esp, system: &mut system, config: &document, cmdline: options.cmdline };
+    let time = clock_get(CLOCK_ID_REALTIME).unwrap_or(0);
+    let result = Installer::new(&mut disk, &mut report).install(&layout, &mut images, time);
+    print(STDOUT, "\n");
+    result.map_err(describe)?;
+
+    print(STDOUT, &format!("system:       {}\n", layout.partition(SYSTEM_A).guid));
+    print(STDOUT, &format!("data:         {}\n", layout.partition(DATA).guid));
+    print(STDOUT, &format!("boot control: {}\n", layout.partition(BOOT_CONTROL).guid));
+    print(STDOUT, "installation complete, the disk can be booted\n");
+    Ok(())
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let (command, rest) = match args {
+        [_, command, rest @ ..] => (*command, rest),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let (driver, result) = match (command, rest) {
+        ("list", []) => return list(),
+        ("plan", [driver]) => (
+            *driver,
+            BlockDisk::open(driver)
+                .map_err(|status| format!("not a disk (error {})", status))
+                .and_then(|disk| {
+                    let (esp, system) = open_images()?;
+                    make_layout(&disk, &esp, &system)
+                })
+                .map(|layout| print_layout(&layout)),
+        ),
+        ("install", [driver, options @ ..]) => match parse_options(options) {
+            Some(options) => (*driver, install(driver, &options)),
+            None => {
+                print(STDERR, USAGE);
+                return EXIT_USAGE;
+            }
+        },
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(message) => {
+            print(STDERR, &format!("orion-install: {}: {}\n", driver, message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
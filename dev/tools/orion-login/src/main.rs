@@ -0,0 +1,339 @@
+/*
+ * Orion Operating System - Console Login
+ *
+ * Asks for a name and a password on a terminal and starts the user's
+ * shell once the identity server accepted them:
+ *
+ *   orion-login [--tty <device>]
+ *
+ * The terminal is a character device found through devfs, ttyS0 unless
+ * `--tty` names another. The device does not echo, so the name is echoed
+ * here as it is typed and the password is not. The shell is started
+ * suspended and attached to the login's identity session before it runs,
+ * with what the user's groups grant (see services/identity); it opens
+ * the terminal itself. When it exits the session is logged out and the
+ * prompt comes back.
+ *
+ * orion-login is started by init with CAP_ADMIN on the identity server,
+ * and only returns if the terminal cannot be opened.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_chardev::{CharClient, DevfsClient};
+use orion_crypto::wipe;
+use orion_ipc::IpcChannel;
+use orion_sys::{close, kill, open, read, resume, spawn_suspended, wait, write, O_RDONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDERR: u64 = 2;
+
+const DEFAULT_TTY: &str = "ttyS0";
+
+// Identity front end requests (see services/identity/src/protocol.rs)
+const IDENTITY_OP_LOGIN: u32 = 0x10;
+const IDENTITY_OP_ATTACH: u32 = 0x12;
+const IDENTITY_OP_LOGOUT: u32 = 0x13;
+
+/// Size of the fs server's key identifiers in a login reply
+const KEY_IDENTIFIER_SIZE: usize = 16;
+
+/// Longest name or password read from the terminal
+const MAX_LINE: usize = 1024;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EIO: i32 = -5;
+const STATUS_EAGAIN: i32 = -11;
+const STATUS_EACCES: i32 = -13;
+const STATUS_EINVAL: i32 = -22;
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-login [--tty <device>]
+";
+
+// Line editing keys
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// IPC channel to a server or a device driver
+struct Ipc(IpcChannel);
+
+impl orion_chardev::Transport for Ipc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// What the identity server answers to a login
+struct Login {
+    session: u64,
+    shell: String,
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EPERM => String::from("permission denied"),
+        STATUS_ENOENT => String::from("not found"),
+        STATUS_EIO => String::from("I/O error"),
+        STATUS_EINVAL => String::from("invalid argument"),
+        status => format!("error {}", status),
+    }
+}
+
+/// Send a request to the identity server, returning the reply payload of
+/// a successful call
+fn call(request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = IpcChannel::connect("identity").call(request).map_err(|_| STATUS_EIO)?;
+    if response.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn with_bytes(request: &mut Vec<u8>, data: &[u8]) {
+    request.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    request.extend_from_slice(data);
+}
+
+fn read_string(data: &[u8], offset: &mut usize) -> Option<String> {
+    let length = u32::from_le_bytes(data.get(*offset..*offset + 4)?.try_into().ok()?) as usize;
+    let bytes = data.get(*offset + 4..*offset + 4 + length)?;
+    *offset += 4 + length;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// A terminal opened through its driver
+struct Terminal {
+    client: CharClient<Ipc>,
+    handle: u32,
+}
+
+impl Terminal {
+    fn open(device: &str) -> Result<Self, i32> {
+        let (_, endpoint) = DevfsClient::new(Ipc(IpcChannel::connect("fs"))).lookup(device)?;
+        let mut client = CharClient::new(Ipc(IpcChannel::connect(&endpoint)));
+        let handle = client.open(0)?;
+        Ok(Self { client, handle })
+    }
+
+    fn print(&mut self, text: &str) {
+        let _ = self.client.write(self.handle, text.as_bytes());
+    }
+
+    /// A line typed on the terminal, echoed if `echo`; None once the
+    /// terminal hung up
+    fn read_line(&mut self, echo: bool) -> Option<Vec<u8>> {
+        let mut line = Vec::new();
+        loop {
+            let input = self.client.read(self.handle, 1).ok()?;
+            let &byte = input.first()?;
+            match byte {
+                b'\r' | b'\n' => {
+                    self.print("\r\n");
+                    return Some(line);
+                }
+                BACKSPACE | DELETE => {
+                    if line.pop().is_some() && echo {
+                        self.print("\x08 \x08");
+                    }
+                }
+                byte if byte.is_ascii_control() || line.len() >= MAX_LINE => {}
+                byte => {
+                    line.push(byte);
+                    if echo {
+                        let _ = self.client.write(self.handle, &[byte]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn close(mut self) {
+        let _ = self.client.close(self.handle);
+    }
+}
+
+/// Check `name` and `password` with the identity server
+fn login(device: &str, name: &[u8], password: &[u8]) -> Result<Login, i32> {
+    let mut request = IDENTITY_OP_LOGIN.to_le_bytes().to_vec();
+    with_bytes(&mut request, name);
+    with_bytes(&mut request, password);
+    with_bytes(&mut request, device.as_bytes());
+    let result = call(&request);
+    wipe(&mut request);
+
+    let reply = result?;
+    let session =
+        reply.get(..8).and_then(|session| session.try_into().ok()).map(u64::from_le_bytes).ok_or(STATUS_EIO)?;
+    let mut offset = 12 + KEY_IDENTIFIER_SIZE;
+    let shell = read_string(&reply, &mut offset).and_then(|_home| read_string(&reply, &mut offset));
+    match shell {
+        Some(shell) => Ok(Login { session, shell }),
+        None => {
+            logout(session);
+            Err(STATUS_EIO)
+        }
+    }
+}
+
+fn logout(session: u64) {
+    let mut request = IDENTITY_OP_LOGOUT.to_le_bytes().to_vec();
+    request.extend_from_slice(&session.to_le_bytes());
+    let _ = call(&request);
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, i32> {
+    let fd = open(path, O_RDONLY)?;
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Ok(data),
+            Ok(count) => data.extend_from_slice(&chunk[..count]),
+            Err(status) => break Err(status),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+/// Run the shell of `login` in its session until it exits
+fn run_shell(login: &Login) -> Result<i32, i32> {
+    if login.shell.is_empty() {
+        return Err(STATUS_ENOENT);
+    }
+    let image = read_file(&login.shell)?;
+    let name = login.shell.rsplit('/').next().unwrap_or(&login.shell);
+    let pid = spawn_suspended(name, &image)?;
+
+    // The shell gets the rights of the user before it runs
+    let mut request = IDENTITY_OP_ATTACH.to_le_bytes().to_vec();
+    request.extend_from_slice(&login.session.to_le_bytes());
+    request.extend_from_slice(&pid.to_le_bytes());
+    if let Err(status) = call(&request).and_then(|_| resume(pid)) {
+        let _ = kill(pid);
+        return Err(status);
+    }
+    wait(pid)
+}
+
+/// Prompt for a name and a password and check them; None when no name
+/// was given
+fn ask(terminal: &mut Terminal, device: &str) -> Option<Result<Login, i32>> {
+    terminal.print("\r\nlogin: ");
+    let name = terminal.read_line(true).filter(|name| !name.is_empty())?;
+    terminal.print("password: ");
+    let mut password = terminal.read_line(false)?;
+    let result = login(device, &name, &password);
+    wipe(&mut password);
+    Some(result)
+}
+
+/// One login on `device`, from the prompt to the end of the shell
+fn session(device: &str) -> Result<(), i32> {
+    let mut terminal = Terminal::open(device)?;
+    let login = match ask(&mut terminal, device) {
+        Some(Ok(login)) => login,
+        Some(Err(status)) => {
+            let message = match status {
+                STATUS_EACCES => String::from("Login incorrect"),
+                STATUS_EAGAIN => String::from("Too many failed logins, try again later"),
+                status => format!("orion-login: {}", describe(status)),
+            };
+            terminal.print(&format!("{}\r\n", message));
+            terminal.close();
+            return Ok(());
+        }
+        None => {
+            terminal.close();
+            return Ok(());
+        }
+    };
+
+    // The shell opens the terminal itself
+    terminal.close();
+    let result = run_shell(&login);
+    logout(login.session);
+    if let Err(status) = result {
+        let mut terminal = Terminal::open(device)?;
+        terminal.print(&format!("orion-login: {}: {}\r\n", login.shell, describe(status)));
+        terminal.close();
+    }
+    Ok(())
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let device = match args.get(1..).unwrap_or(&[]) {
+        [] => DEFAULT_TTY,
+        ["--tty", device] => device,
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    loop {
+        if let Err(status) = session(device) {
+            print(STDERR, &format!("orion-login: {}: {}\n", device, describe(status)));
+            return EXIT_FAILURE;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,264 @@
+/*
+ * Orion Operating System - Mandatory Access Control Tool
+ *
+ * Front end of the kernel MAC policy (see capabilities/mac.h):
+ *
+ *   orion-macctl load <policy>
+ *   orion-macctl mode enforcing|permissive
+ *   orion-macctl status
+ *   orion-macctl denials
+ *   orion-macctl learn
+ *
+ * `load` compiles a policy written in text (see lib/orion_mac) and
+ * replaces the running one; processes keep their labels by name and the
+ * recorded denials are forgotten. `mode` switches between enforcing and
+ * permissive without reloading. `status` shows the mode and counters,
+ * `denials` the denials recorded since the policy was loaded, and
+ * `learn` prints the `allow` lines that would have let them through, to
+ * be reviewed before going into the policy:
+ *
+ *   orion-macctl mode permissive
+ *   ... run the workload ...
+ *   orion-macctl learn >> /etc/mac/policy
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_mac::{
+    class_name, learn, perm_names, Denial, Policy, PolicyError, Status, DENIAL_SIZE, MODE_DISABLED, MODE_ENFORCING,
+    MODE_PERMISSIVE, STATUS_SIZE,
+};
+use orion_sys::{close, mac_denial, mac_load, mac_set_mode, mac_status, open, read, write, O_RDONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_ENOMEM: i32 = -12;
+const STATUS_EACCES: i32 = -13;
+const STATUS_EINVAL: i32 = -22;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-macctl load <policy>
+       orion-macctl mode enforcing|permissive
+       orion-macctl status
+       orion-macctl denials
+       orion-macctl learn
+";
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EPERM => String::from("permission denied, sandboxed programs may not change the policy"),
+        STATUS_EACCES => String::from("the policy does not allow this program to change it"),
+        STATUS_ENOMEM => String::from("out of memory"),
+        STATUS_EINVAL => String::from("rejected by the kernel"),
+        status => format!("error {}", status),
+    }
+}
+
+fn describe_policy(path: &str, error: PolicyError) -> String {
+    match error {
+        PolicyError::Invalid(line) => format!("{}:{}: invalid statement", path, line),
+        PolicyError::UnknownLabel(line) => format!("{}:{}: label not declared before use", path, line),
+        PolicyError::BadPermission(line) => format!("{}:{}: permission does not apply to the class", path, line),
+        PolicyError::TooMany => format!("{}: more labels, contexts or rules than the kernel holds", path),
+    }
+}
+
+fn mode_name(mode: u32) -> &'static str {
+    match mode {
+        MODE_DISABLED => "disabled",
+        MODE_PERMISSIVE => "permissive",
+        MODE_ENFORCING => "enforcing",
+        _ => "unknown",
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let fd = open(path, O_RDONLY).map_err(|status| format!("{}: error {}", path, status))?;
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Ok(data),
+            Ok(count) => data.extend_from_slice(&chunk[..count]),
+            Err(status) => break Err(format!("{}: error {}", path, status)),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+/// Every denial the kernel recorded, in the order it recorded them
+fn denial_records() -> Result<Vec<Denial>, String> {
+    let mut denials = Vec::new();
+    let mut record = [0u8; DENIAL_SIZE];
+    for index in 0.. {
+        match mac_denial(index, &mut record) {
+            Ok(()) => denials.extend(Denial::decode(&record)),
+            Err(STATUS_ENOENT) => break,
+            Err(status) => return Err(describe(status)),
+        }
+    }
+    Ok(denials)
+}
+
+fn load(path: &str) -> Result<(), String> {
+    let text = read_file(path)?;
+    let text = core::str::from_utf8(&text).map_err(|_| format!("{}: not UTF-8 text", path))?;
+    let policy = Policy::parse(text).map_err(|error| describe_policy(path, error))?;
+    mac_load(&policy.encode()).map_err(|status| format!("{}: {}", path, describe(status)))?;
+    print(
+        STDOUT,
+        &format!(
+            "{} loaded: {} labels, {} contexts, {} rules, {}\n",
+            path,
+            policy.labels.len(),
+            policy.subjects.len() + policy.objects.len(),
+            policy.rules.len(),
+            if policy.enforcing { "enforcing" } else { "permissive" }
+        ),
+    );
+    Ok(())
+}
+
+fn set_mode(mode: &str) -> Result<(), String> {
+    let value = match mode {
+        "enforcing" => MODE_ENFORCING,
+        "permissive" => MODE_PERMISSIVE,
+        _ => return Err(format!("unknown mode {}, expected enforcing or permissive", mode)),
+    };
+    mac_set_mode(value).map_err(|status| match status {
+        STATUS_ENOENT => String::from("no policy loaded"),
+        status => describe(status),
+    })
+}
+
+fn status() -> Result<(), String> {
+    let mut record = [0u8; STATUS_SIZE];
+    mac_status(&mut record).map_err(describe)?;
+    let Some(status) = Status::decode(&record) else {
+        return Err(String::from("short status record"));
+    };
+    print(STDOUT, &format!("mode        {}\n", mode_name(status.mode)));
+    if status.mode == MODE_DISABLED {
+        return Ok(());
+    }
+    print(STDOUT, &format!("policy      #{}, {} labels, {} rules\n", status.generation, status.labels, status.rules));
+    print(STDOUT, &format!("labeled     {} processes\n", status.processes));
+    print(STDOUT, &format!("checks      {}\n", status.checks));
+    print(STDOUT, &format!("denials     {}\n", status.denials));
+    Ok(())
+}
+
+fn denials() -> Result<(), String> {
+    print(
+        STDOUT,
+        &format!("{:>8}  {:<16}  {:<16}  {:<8}  {:<20}  FIRST\n", "COUNT", "SUBJECT", "OBJECT", "CLASS", "PERMS"),
+    );
+    for denial in denial_records()? {
+        let perms: Vec<&str> = perm_names(denial.perms).collect();
+        print(
+            STDOUT,
+            &format!(
+                "{:>8}  {:<16}  {:<16}  {:<8}  {:<20}  {}\n",
+                denial.count,
+                denial.subject,
+                denial.object,
+                class_name(denial.class),
+                perms.join(","),
+                denial.example
+            ),
+        );
+    }
+    Ok(())
+}
+
+fn learned() -> Result<(), String> {
+    let denials = denial_records()?;
+    if denials.is_empty() {
+        print(STDERR, "orion-macctl: no denials recorded\n");
+        return Ok(());
+    }
+    print(STDOUT, &learn(&denials));
+    Ok(())
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let result = match args {
+        [_, "load", path] => load(path),
+        [_, "mode", mode] => set_mode(mode),
+        [_, "status"] => status(),
+        [_, "denials"] => denials(),
+        [_, "learn"] => learned(),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(message) => {
+            print(STDERR, &format!("orion-macctl: {}\n", message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
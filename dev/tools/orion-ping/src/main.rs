@@ -0,0 +1,380 @@
+/*
+ * Orion Operating System - Ping Tool
+ *
+ * Sends ICMP echo requests through the network server and reports the
+ * replies:
+ *
+ *   orion-ping [-4|-6] [-c count] [-i interval] [-s size] [-t ttl]
+ *              [-W timeout] <address>
+ *
+ * Requests go out every `interval` seconds (default 1, fractions
+ * allowed) until `count` were sent, then the tool waits up to `timeout`
+ * seconds (default 2) for the last replies and prints round-trip
+ * statistics and packet loss. Without `-c` it runs until killed. `size`
+ * is the echo data length (default 56). The address is dotted IPv4; an
+ * IPv6 address is accepted but reported unsupported until the network
+ * server serves ICMPv6. Needs the raw network capability.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_http::socket::NetChannel;
+use orion_ipc::IpcChannel;
+use orion_ping::socket::{MAX_PAYLOAD, STATUS_EAFNOSUPPORT, STATUS_EHOSTUNREACH, STATUS_EPERM};
+use orion_ping::{EchoKind, EchoMessage, EchoSocket, Family, PingStats, ReplyOutcome};
+use orion_sys::{clock_get, nanosleep, write};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// Pause between two polls of the echo socket
+const POLL_INTERVAL_NS: u64 = 1_000_000;
+
+const DEFAULT_SIZE: usize = 56;
+const DEFAULT_TIMEOUT_NS: u64 = 2 * NS_PER_SEC;
+const MIN_INTERVAL_NS: u64 = 10_000_000;
+
+const EXIT_OK: i32 = 0;
+const EXIT_NO_REPLY: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-ping [-4|-6] [-c count] [-i interval] [-s size] [-t ttl] [-W timeout] <address>
+";
+
+/// IPC channel to the network server
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn now_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+struct Options<'a> {
+    family: Option<Family>,
+    count: Option<u32>,
+    interval_ns: u64,
+    size: usize,
+    ttl: u8,
+    timeout_ns: u64,
+    address: &'a str,
+}
+
+/// Seconds with an optional fraction ("1", "0.2") in nanoseconds
+fn parse_seconds(text: &str) -> Option<u64> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if (whole.is_empty() && fraction.is_empty()) || fraction.len() > 9 {
+        return None;
+    }
+    let seconds: u64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let mut nanos = 0u64;
+    for (index, digit) in fraction.bytes().enumerate() {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        nanos += (digit - b'0') as u64 * 10u64.pow(8 - index as u32);
+    }
+    seconds.checked_mul(NS_PER_SEC)?.checked_add(nanos)
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let mut options = Options {
+        family: None,
+        count: None,
+        interval_ns: NS_PER_SEC,
+        size: DEFAULT_SIZE,
+        ttl: 0,
+        timeout_ns: DEFAULT_TIMEOUT_NS,
+        address: "",
+    };
+    let mut index = 1;
+    while index < args.len() {
+        match args[index] {
+            "-4" => options.family = Some(Family::Inet),
+            "-6" => options.family = Some(Family::Inet6),
+            "-c" => {
+                index += 1;
+                options.count = Some(args.get(index)?.parse().ok().filter(|count| *count > 0)?);
+            }
+            "-i" => {
+                index += 1;
+                options.interval_ns = parse_seconds(args.get(index)?)?.max(MIN_INTERVAL_NS);
+            }
+            "-s" => {
+                index += 1;
+                options.size = args.get(index)?.parse().ok().filter(|size| *size <= MAX_PAYLOAD)?;
+            }
+            "-t" => {
+                index += 1;
+                options.ttl = args.get(index)?.parse().ok().filter(|ttl| *ttl > 0)?;
+            }
+            "-W" => {
+                index += 1;
+                options.timeout_ns = parse_seconds(args.get(index)?)?;
+            }
+            address if options.address.is_empty() && !address.starts_with('-') => options.address = address,
+            _ => return None,
+        }
+        index += 1;
+    }
+    (!options.address.is_empty()).then_some(options)
+}
+
+fn parse_ipv4(text: &str) -> Option<u32> {
+    let mut octets = [0u8; 4];
+    let mut parts = text.split('.');
+    for octet in octets.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        *octet = part.parse().ok()?;
+    }
+    parts.next().is_none().then(|| u32::from_be_bytes(octets))
+}
+
+fn format_ipv4(address: u32) -> String {
+    let [a, b, c, d] = address.to_be_bytes();
+    format!("{}.{}.{}.{}", a, b, c, d)
+}
+
+/// Milliseconds with three decimals
+fn format_ms(ns: u64) -> String {
+    format!("{}.{:03}", ns / 1_000_000, ns % 1_000_000 / 1000)
+}
+
+fn describe_error(kind: EchoKind) -> String {
+    let text = match kind {
+        EchoKind::Unreachable(0) => "Destination Net Unreachable",
+        EchoKind::Unreachable(1) => "Destination Host Unreachable",
+        EchoKind::Unreachable(3) => "Destination Port Unreachable",
+        EchoKind::Unreachable(4) => "Fragmentation Needed",
+        EchoKind::Unreachable(13) => "Communication Administratively Prohibited",
+        EchoKind::Unreachable(code) => return format!("Destination Unreachable (code {})", code),
+        EchoKind::TimeExceeded(_) => "Time to live exceeded",
+        EchoKind::Other { kind, code } => return format!("ICMP type {} code {}", kind, code),
+        EchoKind::Reply => "Echo Reply",
+    };
+    String::from(text)
+}
+
+fn report(message: &EchoMessage, stats: &mut PingStats, now: u64) {
+    let source = format_ipv4(message.source);
+    if message.kind != EchoKind::Reply {
+        if stats.error(message.sequence) {
+            print(
+                STDOUT,
+                &format!(
+                    "From {} icmp_seq={} {}\n",
+                    source,
+                    message.sequence,
+                    describe_error(message.kind)
+                ),
+            );
+        }
+        return;
+    }
+    let line = match stats.reply(message.sequence, now) {
+        ReplyOutcome::Answered { rtt_ns } => format!(
+            "{} bytes from {}: icmp_seq={} ttl={} time={} ms\n",
+            message.data.len() + 8,
+            source,
+            message.sequence,
+            message.ttl,
+            format_ms(rtt_ns)
+        ),
+        ReplyOutcome::Duplicate => {
+            format!(
+                "{} bytes from {}: icmp_seq={} ttl={} (DUP!)\n",
+                message.data.len() + 8,
+                source,
+                message.sequence,
+                message.ttl
+            )
+        }
+        ReplyOutcome::Unexpected => return,
+    };
+    print(STDOUT, &line);
+}
+
+fn summary(address: &str, stats: &PingStats) {
+    let mut text = format!(
+        "\n--- {} ping statistics ---\n{} packets transmitted, {} received",
+        address,
+        stats.transmitted(),
+        stats.received()
+    );
+    if stats.duplicates() > 0 {
+        text.push_str(&format!(", +{} duplicates", stats.duplicates()));
+    }
+    if stats.errors() > 0 {
+        text.push_str(&format!(", +{} errors", stats.errors()));
+    }
+    text.push_str(&format!(", {}% packet loss\n", stats.loss_percent()));
+    if let (Some(min), Some(avg), Some(max), Some(mdev)) =
+        (stats.rtt_min(), stats.rtt_avg(), stats.rtt_max(), stats.rtt_mdev())
+    {
+        text.push_str(&format!(
+            "rtt min/avg/max/mdev = {}/{}/{}/{} ms\n",
+            format_ms(min),
+            format_ms(avg),
+            format_ms(max),
+            format_ms(mdev)
+        ));
+    }
+    print(STDOUT, &text);
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let options = match parse_options(args) {
+        Some(options) => options,
+        None => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let looks_v6 = options.address.contains(':');
+    let family = options
+        .family
+        .unwrap_or(if looks_v6 { Family::Inet6 } else { Family::Inet });
+    let destination = match family {
+        Family::Inet => parse_ipv4(options.address),
+        // Nothing to send to until the server speaks ICMPv6; OPEN says so
+        Family::Inet6 => looks_v6.then_some(0),
+    };
+    let Some(destination) = destination else {
+        print(
+            STDERR,
+            &format!("orion-ping: {}: not an address of that family\n", options.address),
+        );
+        return EXIT_USAGE;
+    };
+
+    let mut socket = match EchoSocket::open(NetIpc(IpcChannel::connect("net")), family) {
+        Ok(socket) => socket,
+        Err(STATUS_EPERM) => {
+            print(STDERR, "orion-ping: the raw network capability is required\n");
+            return EXIT_USAGE;
+        }
+        Err(STATUS_EAFNOSUPPORT) => {
+            print(STDERR, "orion-ping: IPv6 is not supported by the network server yet\n");
+            return EXIT_USAGE;
+        }
+        Err(status) => {
+            print(
+                STDERR,
+                &format!("orion-ping: cannot open an echo socket ({})\n", status),
+            );
+            return EXIT_NO_REPLY;
+        }
+    };
+
+    print(
+        STDOUT,
+        &format!("PING {} {} data bytes\n", options.address, options.size),
+    );
+    let data: Vec<u8> = (0..options.size).map(|index| index as u8).collect();
+    let mut stats = PingStats::new();
+    let mut sequence: u16 = 1;
+    let mut next_send = now_ns();
+    let mut last_sent = 0;
+
+    loop {
+        let now = now_ns();
+        let done_sending = options.count.is_some_and(|count| stats.transmitted() >= count);
+        if !done_sending && now >= next_send {
+            match socket.send(destination, sequence, options.ttl, &data) {
+                Ok(()) => stats.sent(sequence, now),
+                Err(STATUS_EHOSTUNREACH) => {
+                    stats.sent(sequence, now);
+                    stats.error(sequence);
+                    print(STDOUT, &format!("icmp_seq={} Network is unreachable\n", sequence));
+                }
+                Err(status) => {
+                    print(STDERR, &format!("orion-ping: send failed ({})\n", status));
+                    break;
+                }
+            }
+            sequence = sequence.wrapping_add(1);
+            next_send = now + options.interval_ns;
+            last_sent = now;
+        }
+
+        while let Ok(Some(message)) = socket.recv() {
+            report(&message, &mut stats, now_ns());
+        }
+        stats.expire(now_ns(), options.timeout_ns);
+
+        if done_sending && (stats.outstanding() == 0 || now_ns() - last_sent >= options.timeout_ns) {
+            break;
+        }
+        let _ = nanosleep(POLL_INTERVAL_NS);
+    }
+
+    summary(options.address, &stats);
+    if stats.received() > 0 {
+        EXIT_OK
+    } else {
+        EXIT_NO_REPLY
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -1,7 +1,16 @@
 /*
  * Orion Operating System - PS Tool
  *
- * Process listing and management tool for Orion OS.
+ * Lists processes with their memory use:
+ *
+ *   orion-ps [-s rss|uss|pss]
+ *
+ * RSS counts every resident page of a process. Pages shared with other
+ * processes (after fork, until one side writes) are split out: USS is
+ * the memory only this process maps, what killing it would free, and PSS
+ * adds each shared page divided by the number of processes mapping it,
+ * so the PSS of all processes sums to the memory actually in use. `-s`
+ * sorts by one of these, largest first; the default order is by PID.
  *
  * Developed by Jeremy Noverraz (1988-2025)
  * August 2025, Lausanne, Switzerland
@@ -13,8 +22,155 @@
 #![no_std]
 #![no_main]
 
-fn main() {
-    // TODO: Implement process listing tool
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_sys::{proc_info, write, ProcInfo};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-ps [-s rss|uss|pss]
+";
+
+#[derive(Clone, Copy)]
+enum SortKey {
+    Pid,
+    Rss,
+    Uss,
+    Pss,
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn parse_options(args: &[&str]) -> Option<SortKey> {
+    match args.get(1..)? {
+        [] => Some(SortKey::Pid),
+        ["-s", key] => match *key {
+            "rss" => Some(SortKey::Rss),
+            "uss" => Some(SortKey::Uss),
+            "pss" => Some(SortKey::Pss),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Every process, in PID order
+fn processes() -> Vec<ProcInfo> {
+    let mut list = Vec::new();
+    let mut after = 0;
+    loop {
+        let mut info = ProcInfo::default();
+        match proc_info(after, &mut info) {
+            Ok(pid) => {
+                after = pid;
+                list.push(info);
+            }
+            Err(_) => return list,
+        }
+    }
+}
+
+fn state_name(state: u32) -> &'static str {
+    match state {
+        0 => "new",
+        1 => "ready",
+        2 => "run",
+        3 => "wait",
+        4 => "exit",
+        5 => "zombie",
+        _ => "?",
+    }
+}
+
+/// Bytes as KiB, the unit every column uses
+fn kib(bytes: u64) -> u64 {
+    bytes / 1024
+}
+
+fn process_name(info: &ProcInfo) -> &str {
+    let length = info.name.iter().position(|&byte| byte == 0).unwrap_or(info.name.len());
+    core::str::from_utf8(&info.name[..length]).unwrap_or("?")
+}
+
+fn run(args: &[&str]) -> i32 {
+    let sort = match parse_options(args) {
+        Some(sort) => sort,
+        None => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut list = processes();
+    if list.is_empty() {
+        print(STDERR, "orion-ps: cannot list processes\n");
+        return EXIT_FAILURE;
+    }
+    match sort {
+        SortKey::Pid => {}
+        SortKey::Rss => list.sort_by(|a, b| b.rss.cmp(&a.rss)),
+        SortKey::Uss => list.sort_by(|a, b| b.uss.cmp(&a.uss)),
+        SortKey::Pss => list.sort_by(|a, b| b.pss.cmp(&a.pss)),
+    }
+
+    let mut output = String::from("  PID  PPID STATE  THR    RSS(K)    USS(K)    PSS(K) SHARED(K) NAME\n");
+    let mut total_pss = 0;
+    for info in &list {
+        total_pss += info.pss;
+        output.push_str(&format!(
+            "{:>5} {:>5} {:<6} {:>3} {:>9} {:>9} {:>9} {:>9} {}\n",
+            info.pid,
+            info.ppid,
+            state_name(info.state),
+            info.threads,
+            kib(info.rss),
+            kib(info.uss),
+            kib(info.pss),
+            kib(info.shared),
+            process_name(info)
+        ));
+    }
+    output.push_str(&format!("{} processes, {} KiB in use (sum of PSS)\n", list.len(), kib(total_pss)));
+    print(STDOUT, &output);
+    EXIT_OK
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
 }
 
 #[panic_handler]
@@ -0,0 +1,430 @@
+/*
+ * Orion Operating System - Remote Shell Client
+ *
+ * Runs one command on a remote Orion host through its rshell server:
+ *
+ *   orion-rsh --ca handle --host-key hex --key handle --public hex
+ *             [-p port] <ip> <command>
+ *
+ * The connection is TLS, checked against the CA certificate under
+ * keyring handle `--ca`. Once the handshake is over the client exports
+ * the session binding ("EXPORTER-orion-rsh", 32 bytes) and reads HELLO.
+ * The host key in HELLO must be the pinned `--host-key`, and its
+ * signature over "orion-rsh host\0" || challenge || binding must verify.
+ * The client then signs "orion-rsh client\0" || challenge || host key
+ * || binding with the Ed25519 key under keyring handle `--key`, sends
+ * AUTH with `--public`, and runs the command on an EXEC channel. The
+ * command's output goes to stdout and its exit status becomes ours.
+ * Frames are described in services/rshell/src/wire.rs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{self, PUBLIC_KEY_SIZE, SIGNATURE_SIZE};
+use orion_http::socket::{NetChannel, NetStream, STATUS_EAGAIN};
+use orion_http::{IoError, Transport};
+use orion_ipc::IpcChannel;
+use orion_sys::{nanosleep, write};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const DEFAULT_PORT: u16 = 2022;
+
+/// Pause between two polls of an idle connection
+const POLL_INTERVAL_NS: u64 = 1_000_000;
+
+/// Polls before the handshake or a reply is given up on (10 seconds)
+const MAX_IDLE_POLLS: u32 = 10_000;
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+/// Keyring SIGN request opcode (see services/keyring/src/protocol.rs)
+const KEYRING_OP_SIGN: u32 = 9;
+const KEYRING_STATUS_OK: u32 = 0;
+
+// Protocol of services/rshell/src/wire.rs and keys.rs
+const PROTOCOL_VERSION: u32 = 1;
+const FRAME_HELLO: u8 = 1;
+const FRAME_AUTH: u8 = 2;
+const FRAME_AUTH_OK: u8 = 3;
+const FRAME_AUTH_FAILED: u8 = 4;
+const FRAME_OPEN: u8 = 10;
+const FRAME_OPEN_OK: u8 = 11;
+const FRAME_OPEN_FAILED: u8 = 12;
+const FRAME_DATA: u8 = 13;
+const FRAME_EOF: u8 = 15;
+const FRAME_EXIT: u8 = 16;
+const FRAME_CLOSE: u8 = 17;
+const KIND_EXEC: u8 = 2;
+const FRAME_HEADER_SIZE: usize = 9;
+const MAX_PAYLOAD: usize = 32 * 1024;
+const CHALLENGE_SIZE: usize = 32;
+const EXPORTER_LABEL: &str = "EXPORTER-orion-rsh";
+const BINDING_SIZE: usize = 32;
+const HOST_CONTEXT: &[u8] = b"orion-rsh host\0";
+const CLIENT_CONTEXT: &[u8] = b"orion-rsh client\0";
+
+/// Channel the command runs on
+const EXEC_CHANNEL: u32 = 1;
+
+const USAGE: &str = "\
+usage: orion-rsh --ca handle --host-key hex --key handle --public hex [-p port] <ip> <command>
+";
+
+/// IPC channel to the network server
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+struct Options<'a> {
+    trust_anchor: u64,
+    host_key: [u8; PUBLIC_KEY_SIZE],
+    key_handle: u64,
+    public_key: [u8; PUBLIC_KEY_SIZE],
+    port: u16,
+    address: &'a str,
+    command: &'a str,
+}
+
+/// A frame read from the server
+struct Frame {
+    kind: u8,
+    channel: u32,
+    payload: Vec<u8>,
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn fail(message: &str) -> i32 {
+    print(STDERR, &format!("orion-rsh: {}\n", message));
+    EXIT_FAILURE
+}
+
+fn poll_wait() {
+    let _ = nanosleep(POLL_INTERVAL_NS);
+}
+
+fn parse_ipv4(text: &str) -> Option<u32> {
+    let mut octets = [0u8; 4];
+    let mut parts = text.split('.');
+    for octet in octets.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        *octet = part.parse().ok()?;
+    }
+    parts.next().is_none().then(|| u32::from_be_bytes(octets))
+}
+
+fn parse_key(text: &str) -> Option<[u8; PUBLIC_KEY_SIZE]> {
+    if text.len() != PUBLIC_KEY_SIZE * 2 || !text.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; PUBLIC_KEY_SIZE];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let (mut trust_anchor, mut host_key, mut key_handle, mut public_key) = (None, None, None, None);
+    let mut port = DEFAULT_PORT;
+    let mut positional = Vec::new();
+    let mut index = 1;
+    while index < args.len() {
+        match args[index] {
+            "--ca" => {
+                index += 1;
+                trust_anchor = Some(args.get(index)?.parse().ok()?);
+            }
+            "--host-key" => {
+                index += 1;
+                host_key = Some(parse_key(args.get(index)?)?);
+            }
+            "--key" => {
+                index += 1;
+                key_handle = Some(args.get(index)?.parse().ok()?);
+            }
+            "--public" => {
+                index += 1;
+                public_key = Some(parse_key(args.get(index)?)?);
+            }
+            "-p" => {
+                index += 1;
+                port = args.get(index)?.parse().ok()?;
+            }
+            argument if !argument.starts_with('-') => positional.push(argument),
+            _ => return None,
+        }
+        index += 1;
+    }
+    match positional[..] {
+        [address, command] => Some(Options {
+            trust_anchor: trust_anchor?,
+            host_key: host_key?,
+            key_handle: key_handle?,
+            public_key: public_key?,
+            port,
+            address,
+            command,
+        }),
+        _ => None,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn encode(frame_type: u8, channel: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    frame.push(frame_type);
+    frame.extend_from_slice(&channel.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Frames of one connection, with what was read past the last one
+struct Connection {
+    stream: NetStream<NetIpc>,
+    inbound: Vec<u8>,
+}
+
+impl Connection {
+    fn send(&mut self, frame: &[u8]) -> Result<(), &'static str> {
+        let mut sent = 0;
+        let mut idle = 0;
+        while sent < frame.len() {
+            match self.stream.write(&frame[sent..]) {
+                Ok(written) => {
+                    sent += written;
+                    idle = 0;
+                }
+                Err(IoError::WouldBlock) if idle < MAX_IDLE_POLLS => {
+                    idle += 1;
+                    poll_wait();
+                }
+                Err(IoError::WouldBlock) => return Err("timed out sending"),
+                Err(_) => return Err("connection closed"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Next whole frame from the server
+    fn receive(&mut self) -> Result<Frame, &'static str> {
+        let mut buffer = [0u8; 4096];
+        let mut idle = 0;
+        loop {
+            if let Some(length) = read_u32(&self.inbound, 5) {
+                if length as usize > MAX_PAYLOAD {
+                    return Err("oversized frame");
+                }
+                let end = FRAME_HEADER_SIZE + length as usize;
+                if self.inbound.len() >= end {
+                    let frame = Frame {
+                        kind: self.inbound[0],
+                        channel: read_u32(&self.inbound, 1).unwrap_or(0),
+                        payload: self.inbound[FRAME_HEADER_SIZE..end].to_vec(),
+                    };
+                    self.inbound.drain(..end);
+                    return Ok(frame);
+                }
+            }
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err("connection closed"),
+                Ok(count) => {
+                    self.inbound.extend_from_slice(&buffer[..count]);
+                    idle = 0;
+                }
+                Err(IoError::WouldBlock) if idle < MAX_IDLE_POLLS => {
+                    idle += 1;
+                    poll_wait();
+                }
+                Err(IoError::WouldBlock) => return Err("timed out waiting for the host"),
+                Err(_) => return Err("connection closed"),
+            }
+        }
+    }
+
+    /// Keying material of the TLS session, once the handshake is over
+    fn binding(&self) -> Result<[u8; BINDING_SIZE], &'static str> {
+        for _ in 0..MAX_IDLE_POLLS {
+            match self.stream.tls_exporter(EXPORTER_LABEL, BINDING_SIZE as u16) {
+                Ok(binding) => return binding[..].try_into().map_err(|_| "bad TLS exporter value"),
+                Err(STATUS_EAGAIN) => poll_wait(),
+                Err(_) => return Err("TLS handshake failed"),
+            }
+        }
+        Err("timed out in the TLS handshake")
+    }
+}
+
+/// Sign `message` with the key under keyring handle `handle`
+fn keyring_signature(handle: u64, message: &[u8]) -> Option<[u8; SIGNATURE_SIZE]> {
+    let mut request = Vec::with_capacity(12 + message.len());
+    request.extend_from_slice(&KEYRING_OP_SIGN.to_le_bytes());
+    request.extend_from_slice(&handle.to_le_bytes());
+    request.extend_from_slice(message);
+
+    let response = IpcChannel::connect("keyring").call(&request).ok()?;
+    if response.len() < 4 + SIGNATURE_SIZE || response[..4] != KEYRING_STATUS_OK.to_le_bytes() {
+        return None;
+    }
+    response[4..4 + SIGNATURE_SIZE].try_into().ok()
+}
+
+/// Check HELLO against the pinned host key; the challenge to answer
+fn check_hello(
+    hello: &Frame,
+    host_key: &[u8; PUBLIC_KEY_SIZE],
+    binding: &[u8; BINDING_SIZE],
+) -> Result<[u8; CHALLENGE_SIZE], &'static str> {
+    let payload = &hello.payload;
+    if hello.kind != FRAME_HELLO || payload.len() != 4 + PUBLIC_KEY_SIZE + CHALLENGE_SIZE + SIGNATURE_SIZE {
+        return Err("expected HELLO");
+    }
+    if read_u32(payload, 0) != Some(PROTOCOL_VERSION) {
+        return Err("unsupported protocol version");
+    }
+    let (key, rest) = payload[4..].split_at(PUBLIC_KEY_SIZE);
+    let (challenge, signature) = rest.split_at(CHALLENGE_SIZE);
+    if key != host_key {
+        return Err("host key does not match --host-key");
+    }
+    let mut message = HOST_CONTEXT.to_vec();
+    message.extend_from_slice(challenge);
+    message.extend_from_slice(binding);
+    let signature: &[u8; SIGNATURE_SIZE] = signature.try_into().map_err(|_| "expected HELLO")?;
+    if !ed25519::verify(host_key, &message, signature) {
+        return Err("host signature does not verify");
+    }
+    challenge.try_into().map_err(|_| "expected HELLO")
+}
+
+fn session(connection: &mut Connection, options: &Options) -> Result<i32, &'static str> {
+    let binding = connection.binding()?;
+    let hello = connection.receive()?;
+    let challenge = check_hello(&hello, &options.host_key, &binding)?;
+
+    let mut message = CLIENT_CONTEXT.to_vec();
+    message.extend_from_slice(&challenge);
+    message.extend_from_slice(&options.host_key);
+    message.extend_from_slice(&binding);
+    let signature = keyring_signature(options.key_handle, &message).ok_or("keyring refused to sign")?;
+    let mut auth = Vec::with_capacity(PUBLIC_KEY_SIZE + SIGNATURE_SIZE);
+    auth.extend_from_slice(&options.public_key);
+    auth.extend_from_slice(&signature);
+    connection.send(&encode(FRAME_AUTH, 0, &auth))?;
+    match connection.receive()?.kind {
+        FRAME_AUTH_OK => {}
+        FRAME_AUTH_FAILED => return Err("key not accepted by the host"),
+        _ => return Err("expected AUTH_OK"),
+    }
+
+    let mut open = Vec::with_capacity(5 + options.command.len());
+    open.push(KIND_EXEC);
+    open.extend_from_slice(&(options.command.len() as u32).to_le_bytes());
+    open.extend_from_slice(options.command.as_bytes());
+    connection.send(&encode(FRAME_OPEN, EXEC_CHANNEL, &open))?;
+    // The command reads nothing from us
+    connection.send(&encode(FRAME_EOF, EXEC_CHANNEL, &[]))?;
+
+    let mut status = EXIT_FAILURE;
+    loop {
+        let frame = connection.receive()?;
+        if frame.channel != EXEC_CHANNEL {
+            continue;
+        }
+        match frame.kind {
+            FRAME_OPEN_OK | FRAME_EOF => {}
+            FRAME_OPEN_FAILED => return Err("host refused the command"),
+            FRAME_DATA => {
+                let _ = write(STDOUT, &frame.payload);
+            }
+            FRAME_EXIT => status = read_u32(&frame.payload, 0).map_or(EXIT_FAILURE, |code| code as i32),
+            FRAME_CLOSE => return Ok(status),
+            _ => return Err("unexpected frame"),
+        }
+    }
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let Some(options) = parse_options(args) else {
+        print(STDERR, USAGE);
+        return EXIT_USAGE;
+    };
+    let Some(ip) = parse_ipv4(options.address) else {
+        print(STDERR, "orion-rsh: host must be an IPv4 address\n");
+        return EXIT_USAGE;
+    };
+
+    let channel = NetIpc(IpcChannel::connect("net"));
+    let stream = match NetStream::connect_tls(channel, ip, options.port, options.trust_anchor, options.address) {
+        Ok(stream) => stream,
+        Err(_) => return fail("connection refused"),
+    };
+    let mut connection = Connection { stream, inbound: Vec::new() };
+    let result = session(&mut connection, &options);
+    connection.stream.close();
+    result.unwrap_or_else(fail)
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
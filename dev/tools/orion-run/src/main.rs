@@ -0,0 +1,331 @@
+/*
+ * Orion Operating System - Isolated Launcher
+ *
+ * Runs a program in namespaces of its own, with a restricted sandbox:
+ *
+ *   orion-run [--mount] [--net] [--pid] [--time] [--clocks <ns>] [--root <dir>]
+ *             [--manifest <file>] <program>
+ *
+ * Without any of --mount, --net and --pid the program gets all three
+ * namespaces. In its mount namespace it sees the directory `--root`
+ * (default /) as /, with the mounts found below it, and mounts made there
+ * stay there. In its network namespace it has a single interface bridged
+ * to the host (see services/net/netns.h). In its PID namespace it is
+ * PID 1 and only sees its own descendants, which are killed when it
+ * exits.
+ *
+ * With --time the program gets a time namespace too, whose monotonic and
+ * boot-time clocks start out at `--clocks` nanoseconds (which implies
+ * --time), by default where the host's are. Restoring a checkpointed
+ * program with the clocks it had keeps its timers and timeouts sane.
+ *
+ * The sandbox is the manifest in `--manifest` (see services/io/src/
+ * sandbox.rs), by default one allowing only the basic system calls and
+ * the fs and net servers. orion-run waits for the program and exits with
+ * its exit code.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+use orion_sys::{
+    clock_get, close, kill, ns_info, ns_set_clocks, ns_unshare, open, read, resume, spawn_suspended, wait, write,
+    O_RDONLY,
+};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// Namespace kinds (mirror of namespace.h)
+const NS_MOUNT: u32 = 1 << 0;
+const NS_NET: u32 = 1 << 1;
+const NS_PID: u32 = 1 << 2;
+const NS_TIME: u32 = 1 << 3;
+const NS_ALL: u32 = NS_MOUNT | NS_NET | NS_PID;
+
+// Clocks offset in a time namespace (mirror of wallclock.h)
+const CLOCK_ID_MONOTONIC: u32 = 0;
+const CLOCK_ID_BOOTTIME: u32 = 2;
+
+// File system server requests (see services/fs/src/main.rs)
+const FS_OP_NAMESPACE: u32 = 0x48;
+const FS_OP_NAMESPACE_DROP: u32 = 0x49;
+
+const IO_OP_APPLY_SANDBOX: u32 = 7;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EBUSY: i32 = -16;
+const STATUS_EEXIST: i32 = -17;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOSPC: i32 = -28;
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-run [--mount] [--net] [--pid] [--time] [--clocks <ns>] [--root <dir>]
+                 [--manifest <file>] <program>
+";
+
+/// Sandbox of programs run without --manifest
+const DEFAULT_MANIFEST: &str = "\
+syscalls = process, memory, ipc, time
+ipc = fs, net
+memory = 64M
+on_violation = terminate
+";
+
+struct Options<'a> {
+    namespaces: u32,
+    clocks: Option<u64>,
+    root: &'a str,
+    manifest: Option<&'a str>,
+    program: &'a str,
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let mut options = Options { namespaces: 0, clocks: None, root: "/", manifest: None, program: "" };
+    let mut rest = args.get(1..)?.iter();
+    while let Some(&arg) = rest.next() {
+        match arg {
+            "--mount" => options.namespaces |= NS_MOUNT,
+            "--net" => options.namespaces |= NS_NET,
+            "--pid" => options.namespaces |= NS_PID,
+            "--time" => options.namespaces |= NS_TIME,
+            "--clocks" => {
+                options.clocks = Some(rest.next()?.parse().ok()?);
+                options.namespaces |= NS_TIME;
+            }
+            "--root" => options.root = rest.next()?,
+            "--manifest" => options.manifest = Some(rest.next()?),
+            program if !program.starts_with('-') && rest.len() == 0 => options.program = program,
+            _ => return None,
+        }
+    }
+    if options.program.is_empty() {
+        return None;
+    }
+    // --time alone adds to the default namespaces rather than replacing them
+    if options.namespaces & NS_ALL == 0 {
+        options.namespaces |= NS_ALL;
+    }
+    // Another root only makes sense with a mount namespace to hold it
+    if options.root != "/" && options.namespaces & NS_MOUNT == 0 {
+        return None;
+    }
+    Some(options)
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EPERM => String::from("permission denied"),
+        STATUS_ENOENT => String::from("not found"),
+        STATUS_EBUSY => String::from("clocks already in use"),
+        STATUS_EEXIST => String::from("namespace already set up"),
+        STATUS_EINVAL => String::from("invalid argument"),
+        STATUS_ENOSPC => String::from("no namespace left"),
+        status => format!("error {}", status),
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let fd = open(path, O_RDONLY).map_err(|status| format!("{}: {}", path, describe(status)))?;
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Ok(data),
+            Ok(count) => data.extend_from_slice(&chunk[..count]),
+            Err(status) => break Err(format!("{}: {}", path, describe(status))),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+/// Send a request to a server, returning its status
+fn call(server: &str, request: &[u8]) -> i32 {
+    match IpcChannel::connect(server).call(request) {
+        Ok(response) if response.len() >= 4 => {
+            i32::from_le_bytes([response[0], response[1], response[2], response[3]])
+        }
+        _ => STATUS_ENOENT,
+    }
+}
+
+fn with_string(request: &mut Vec<u8>, text: &str) {
+    request.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    request.extend_from_slice(text.as_bytes());
+}
+
+/// Put the suspended child `pid` in its namespaces and sandbox. Returns
+/// the mount namespace set up in the fs server, to drop once it exits
+fn isolate(pid: u64, options: &Options, manifest: &str) -> Result<Option<u32>, String> {
+    ns_unshare(pid, options.namespaces).map_err(|status| format!("namespaces: {}", describe(status)))?;
+    if let Some(clocks) = options.clocks {
+        let offset = |clock| -> Result<i64, String> {
+            let now = clock_get(clock).map_err(|status| format!("clocks: {}", describe(status)))?;
+            Ok(clocks as i64 - now as i64)
+        };
+        ns_set_clocks(pid, offset(CLOCK_ID_MONOTONIC)?, offset(CLOCK_ID_BOOTTIME)?)
+            .map_err(|status| format!("clocks: {}", describe(status)))?;
+    }
+
+    let mut mount_namespace = None;
+    if options.namespaces & NS_MOUNT != 0 {
+        let info = ns_info(pid).map_err(|status| format!("namespaces: {}", describe(status)))?;
+        let mut request = FS_OP_NAMESPACE.to_le_bytes().to_vec();
+        request.extend_from_slice(&info.mount.to_le_bytes());
+        with_string(&mut request, options.root);
+        match call("fs", &request) {
+            STATUS_OK => mount_namespace = Some(info.mount),
+            status => return Err(format!("{}: {}", options.root, describe(status))),
+        }
+    }
+
+    let mut request = IO_OP_APPLY_SANDBOX.to_le_bytes().to_vec();
+    request.extend_from_slice(&pid.to_le_bytes());
+    with_string(&mut request, manifest);
+    match call("io", &request) {
+        STATUS_OK => Ok(mount_namespace),
+        status => {
+            drop_namespace(mount_namespace);
+            Err(format!("sandbox: {}", describe(status)))
+        }
+    }
+}
+
+fn drop_namespace(namespace: Option<u32>) {
+    if let Some(namespace) = namespace {
+        let mut request = FS_OP_NAMESPACE_DROP.to_le_bytes().to_vec();
+        request.extend_from_slice(&namespace.to_le_bytes());
+        let _ = call("fs", &request);
+    }
+}
+
+fn run_isolated(options: &Options) -> Result<i32, String> {
+    let manifest = match options.manifest {
+        Some(path) => String::from_utf8(read_file(path)?).map_err(|_| format!("{}: not text", path))?,
+        None => String::from(DEFAULT_MANIFEST),
+    };
+    let image = read_file(options.program)?;
+    let name = options.program.rsplit('/').next().unwrap_or(options.program);
+    let pid = spawn_suspended(name, &image).map_err(|status| format!("{}: {}", options.program, describe(status)))?;
+
+    // Nothing of the program runs before it is isolated
+    let mount_namespace = match isolate(pid, options, &manifest) {
+        Ok(namespace) => namespace,
+        Err(message) => {
+            let _ = kill(pid);
+            return Err(message);
+        }
+    };
+    if let Err(status) = resume(pid) {
+        let _ = kill(pid);
+        drop_namespace(mount_namespace);
+        return Err(format!("{}: {}", options.program, describe(status)));
+    }
+    print(STDOUT, &format!("orion-run: {} started as PID {}\n", name, pid));
+
+    let result = wait(pid).map_err(|status| format!("wait: {}", describe(status)));
+    drop_namespace(mount_namespace);
+    result
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let Some(options) = parse_options(args) else {
+        print(STDERR, USAGE);
+        return EXIT_USAGE;
+    };
+    match run_isolated(&options) {
+        Ok(code) => code,
+        Err(message) => {
+            print(STDERR, &format!("orion-run: {}\n", message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_to_every_namespace() {
+        let options = parse_options(&["orion-run", "/bin/sh"]).unwrap();
+        assert_eq!((options.namespaces, options.program), (NS_ALL, "/bin/sh"));
+        let options = parse_options(&["orion-run", "--net", "/bin/sh"]).unwrap();
+        assert_eq!(options.namespaces, NS_NET);
+    }
+
+    #[test]
+    fn test_time_alone_keeps_the_default_namespaces() {
+        let options = parse_options(&["orion-run", "--time", "/bin/sh"]).unwrap();
+        assert_eq!(options.namespaces, NS_ALL | NS_TIME);
+        let options = parse_options(&["orion-run", "--clocks", "1000", "/bin/sh"]).unwrap();
+        assert_eq!((options.namespaces, options.clocks), (NS_ALL | NS_TIME, Some(1000)));
+        let options = parse_options(&["orion-run", "--pid", "--time", "/bin/sh"]).unwrap();
+        assert_eq!(options.namespaces, NS_PID | NS_TIME);
+    }
+
+    #[test]
+    fn test_rejects_a_root_without_a_mount_namespace() {
+        assert!(parse_options(&["orion-run", "--net", "--root", "/srv", "/bin/sh"]).is_none());
+        assert!(parse_options(&["orion-run", "--time", "--root", "/srv", "/bin/sh"]).is_some());
+        assert!(parse_options(&["orion-run", "--time"]).is_none());
+    }
+}
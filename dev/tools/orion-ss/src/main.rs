@@ -0,0 +1,344 @@
+/*
+ * Orion Operating System - Socket Statistics Tool
+ *
+ * Lists the sockets of the network server, in the manner of ss:
+ *
+ *   orion-ss [-t] [-u] [-l | -a] [-i] [--state name]... [--port port]
+ *   orion-ss -s
+ *
+ * Without filters it shows the connected sockets: TCP connections past
+ * the handshake and UDP endpoints with a peer. `-l` shows the listening
+ * ones instead (TCP listeners and unconnected UDP endpoints) and `-a`
+ * both. `-t` and `-u` limit the listing to TCP or UDP, `--state` to TCP
+ * connections in the named states (established, syn-sent, syn-recv,
+ * fin-wait-1, fin-wait-2, time-wait, closed, close-wait, last-ack,
+ * listening, closing; may be repeated) and `--port` to sockets with that
+ * local or remote port. `-i` adds a line per socket with its congestion
+ * control, round-trip time, windows and retransmission counters.
+ *
+ * `-s` prints the socket counts and the stack-wide IP, TCP, UDP and
+ * ICMP counters. A program in a network namespace only sees the
+ * sockets of its namespace.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_http::socket::NetChannel;
+use orion_ipc::IpcChannel;
+use orion_sockdiag::{Filter, Protocol, SockDiag, SocketEntry, Summary, TcpState};
+use orion_sys::write;
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-ss [-t] [-u] [-l | -a] [-i] [--state name]... [--port port]
+       orion-ss -s
+";
+
+/// IPC channel to the network server
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+/// Which side of the listening/connected split to show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Show {
+    Connected,
+    Listening,
+    All,
+}
+
+struct Options {
+    summary: bool,
+    internals: bool,
+    show: Show,
+    filter: Filter,
+}
+
+fn parse_options(args: &[&str]) -> Option<Options> {
+    let mut options = Options {
+        summary: false,
+        internals: false,
+        show: Show::Connected,
+        filter: Filter::default(),
+    };
+    let mut index = 1;
+    while index < args.len() {
+        match args[index] {
+            "-s" => options.summary = true,
+            "-i" => options.internals = true,
+            "-t" => options.filter.protocols.push(Protocol::Tcp),
+            "-u" => options.filter.protocols.push(Protocol::Udp),
+            "-l" if options.show != Show::All => options.show = Show::Listening,
+            "-a" if options.show != Show::Listening => options.show = Show::All,
+            "--state" => {
+                index += 1;
+                options
+                    .filter
+                    .states
+                    .push(TcpState::parse(args.get(index)?)?);
+            }
+            "--port" => {
+                index += 1;
+                options.filter.port = args.get(index)?.parse().ok().filter(|port| *port > 0)?;
+            }
+            _ => return None,
+        }
+        index += 1;
+    }
+    // The summary takes no filter
+    if options.summary && args.len() > 2 {
+        return None;
+    }
+    Some(options)
+}
+
+/// Whether an entry belongs to the listening side: a TCP listener or a
+/// UDP endpoint without a peer
+fn is_listening(entry: &SocketEntry) -> bool {
+    match entry.protocol {
+        Protocol::Tcp => entry.is_listening(),
+        Protocol::Udp => entry.remote_ip == 0 && entry.remote_port == 0,
+    }
+}
+
+fn format_ipv4(address: u32) -> String {
+    let [a, b, c, d] = address.to_be_bytes();
+    format!("{}.{}.{}.{}", a, b, c, d)
+}
+
+fn format_endpoint(address: u32, port: u16) -> String {
+    let host = if address == 0 {
+        String::from("*")
+    } else {
+        format_ipv4(address)
+    };
+    if port == 0 {
+        format!("{}:*", host)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn state_name(entry: &SocketEntry) -> &'static str {
+    match entry.state {
+        Some(state) => state.name(),
+        None if is_listening(entry) => "UNCONN",
+        None => "ESTAB",
+    }
+}
+
+/// Congestion control names, by orion_tcp_cc_algorithm_t
+fn congestion_control_name(algorithm: u8) -> &'static str {
+    match algorithm {
+        0 => "reno",
+        1 => "newreno",
+        2 => "cubic",
+        3 => "bbr",
+        4 => "vegas",
+        5 => "westwood",
+        6 => "htcp",
+        7 => "scalable",
+        8 => "highspeed",
+        _ => "unknown",
+    }
+}
+
+/// Microseconds as milliseconds with three decimals
+fn format_us(us: u32) -> String {
+    format!("{}.{:03}", us / 1000, us % 1000)
+}
+
+fn format_line(entry: &SocketEntry) -> String {
+    format!(
+        "{:<6}{:<12}{:>8} {:>8} {:<22}{}\n",
+        entry.protocol.name(),
+        state_name(entry),
+        entry.recv_queue,
+        entry.send_queue,
+        format_endpoint(entry.local_ip, entry.local_port),
+        format_endpoint(entry.remote_ip, entry.remote_port)
+    )
+}
+
+fn format_internals(entry: &SocketEntry) -> String {
+    match entry.protocol {
+        Protocol::Tcp => format!(
+            "\t {} rtt:{}/{} rto:{} cwnd:{} ssthresh:{} unacked:{} retrans:{} timeouts:{} bytes_sent:{} bytes_received:{}\n",
+            congestion_control_name(entry.congestion_control),
+            format_us(entry.rtt_us),
+            format_us(entry.rttvar_us),
+            entry.rto_ms,
+            entry.cwnd,
+            entry.ssthresh,
+            entry.in_flight,
+            entry.retransmissions,
+            entry.timeouts,
+            entry.bytes_sent,
+            entry.bytes_received
+        ),
+        Protocol::Udp => format!(
+            "\t bytes_sent:{} bytes_received:{} drops:{}\n",
+            entry.bytes_sent, entry.bytes_received, entry.dropped
+        ),
+    }
+}
+
+fn list(diag: &mut SockDiag<NetIpc>, options: &Options) -> i32 {
+    let entries = match diag.list(&options.filter) {
+        Ok(entries) => entries,
+        Err(status) => {
+            print(
+                STDERR,
+                &format!("orion-ss: cannot list sockets ({})\n", status),
+            );
+            return EXIT_FAILURE;
+        }
+    };
+
+    // An explicit state filter already says which sockets to show
+    let by_state = !options.filter.states.is_empty();
+    let mut output = format!(
+        "{:<6}{:<12}{:>8} {:>8} {:<22}{}\n",
+        "Netid", "State", "Recv-Q", "Send-Q", "Local Address:Port", "Peer Address:Port"
+    );
+    for entry in &entries {
+        let shown = by_state
+            || match options.show {
+                Show::Connected => !is_listening(entry),
+                Show::Listening => is_listening(entry),
+                Show::All => true,
+            };
+        if !shown {
+            continue;
+        }
+        output.push_str(&format_line(entry));
+        if options.internals {
+            output.push_str(&format_internals(entry));
+        }
+    }
+    print(STDOUT, &output);
+    EXIT_OK
+}
+
+fn format_summary(summary: &Summary) -> String {
+    let counters = &summary.counters;
+    let mut output = format!(
+        "Total: {}\nTCP:   {} (estab {})\nUDP:   {}\n\n",
+        summary.tcp_sockets + summary.udp_sockets,
+        summary.tcp_sockets,
+        summary.tcp_established,
+        summary.udp_sockets
+    );
+    output.push_str(&format!(
+        "Ip:\n    {} total packets received\n    {} with invalid headers\n    {} delivered\n    {} requests sent out\n    {} outgoing packets dropped\n",
+        counters.ip_in_receives,
+        counters.ip_in_hdr_errors,
+        counters.ip_in_delivers,
+        counters.ip_out_requests,
+        counters.ip_out_discards
+    ));
+    output.push_str(&format!(
+        "Tcp:\n    {} active connection openings\n    {} segments received\n    {} segments sent out\n    {} segments retransmitted\n    {} retransmission timeouts\n",
+        counters.tcp_active_opens,
+        counters.tcp_in_segs,
+        counters.tcp_out_segs,
+        counters.tcp_retrans_segs,
+        counters.tcp_timeouts
+    ));
+    output.push_str(&format!(
+        "Udp:\n    {} packets received\n    {} packets to unknown port received\n    {} packet receive errors\n    {} packets sent\n",
+        counters.udp_in_datagrams, counters.udp_no_ports, counters.udp_in_errors, counters.udp_out_datagrams
+    ));
+    output.push_str(&format!(
+        "Icmp:\n    {} ICMP messages received\n    {} input ICMP message failed\n    {} ICMP messages sent\n",
+        counters.icmp_in_msgs, counters.icmp_in_errors, counters.icmp_out_msgs
+    ));
+    output
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let Some(options) = parse_options(args) else {
+        print(STDERR, USAGE);
+        return EXIT_USAGE;
+    };
+
+    let mut diag = SockDiag::new(NetIpc(IpcChannel::connect("net")));
+    if !options.summary {
+        return list(&mut diag, &options);
+    }
+    match diag.summary() {
+        Ok(summary) => {
+            print(STDOUT, &format_summary(&summary));
+            EXIT_OK
+        }
+        Err(status) => {
+            print(
+                STDERR,
+                &format!("orion-ss: cannot read the stack counters ({})\n", status),
+            );
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
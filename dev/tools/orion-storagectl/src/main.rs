@@ -0,0 +1,314 @@
+/*
+ * Orion Operating System - Storage Control Tool
+ *
+ * Front end of the crash dump server:
+ *
+ *   orion-storagectl crash status
+ *   orion-storagectl crash list [component]
+ *   orion-storagectl crash get <id> <file>
+ *   orion-storagectl crash delete <id>
+ *   orion-storagectl crash retention <dumps> <per-component> <bytes> <days>
+ *
+ * `status` shows how much the store holds and what compression and
+ * deduplication saved, with the retention limits. `list` shows the dumps
+ * kept, oldest first, of one component or of all of them. `get` copies a
+ * dump to a file. `retention` sets the limits, 0 disabling the byte or
+ * the age limit; dumps over them are dropped right away, oldest first.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+use orion_sys::{close, open, write, O_CREAT, O_TRUNC, O_WRONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// Crash dump server requests (see services/crashd/src/protocol.rs)
+const OP_STATUS: u32 = 1;
+const OP_LIST: u32 = 2;
+const OP_READ: u32 = 3;
+const OP_DELETE: u32 = 4;
+const OP_SET_RETENTION: u32 = 5;
+const MAX_READ: u32 = 60 * 1024;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EBUSY: i32 = -16;
+const STATUS_ENODEV: i32 = -19;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOSPC: i32 = -28;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const NS_PER_DAY: u64 = 86_400 * 1_000_000_000;
+
+const USAGE: &str = "\
+usage: orion-storagectl crash status
+       orion-storagectl crash list [component]
+       orion-storagectl crash get <id> <file>
+       orion-storagectl crash delete <id>
+       orion-storagectl crash retention <dumps> <per-component> <bytes> <days>
+";
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+/// Send a request to `channel`, returning the reply payload or the status
+fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = channel.call(request).map_err(|_| STATUS_ENOENT)?;
+    if response.len() < 4 {
+        return Err(STATUS_ENOENT);
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EPERM => String::from("permission denied"),
+        STATUS_ENOENT => String::from("no such dump, or no crash dump server"),
+        STATUS_EBUSY => String::from("the store is busy"),
+        STATUS_ENODEV => String::from("the crash volume is missing"),
+        STATUS_EINVAL => String::from("invalid request"),
+        STATUS_ENOSPC => String::from("the crash volume is full"),
+        status => format!("error {}", status),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8).map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Decode a `len, utf-8 bytes` field, returning it with the offset after it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset) as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from_utf8_lossy(bytes).into_owned(), offset + 4 + len))
+}
+
+fn size_text(bytes: u64) -> String {
+    const KIB: u64 = 1 << 10;
+    const MIB: u64 = 1 << 20;
+    if bytes >= 10 * MIB {
+        format!("{} MiB", bytes / MIB)
+    } else if bytes >= 10 * KIB {
+        format!("{} KiB", bytes / KIB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Realtime clock value as a UTC date and time
+fn time_text(ns: u64) -> String {
+    let seconds = ns / 1_000_000_000;
+    let (days, rest) = (seconds / 86_400, seconds % 86_400);
+    // Civil date from days since 1970-01-01, proleptic Gregorian calendar
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+fn status(channel: &mut IpcChannel) -> Result<(), String> {
+    let record = call(channel, &OP_STATUS.to_le_bytes()).map_err(describe)?;
+    if record.len() < 8 + 6 * 8 + 24 {
+        return Err(String::from("short status reply"));
+    }
+    let (dumps, chunks) = (read_u32(&record, 0), read_u32(&record, 4));
+    let (raw, stored, used, capacity) =
+        (read_u64(&record, 8), read_u64(&record, 16), read_u64(&record, 24), read_u64(&record, 32));
+    let (dedup_hits, evicted) = (read_u64(&record, 40), read_u64(&record, 48));
+    print(STDOUT, &format!("dumps:      {} ({}), {} chunks\n", dumps, size_text(raw), chunks));
+    print(STDOUT, &format!("stored:     {}, {} shared chunks\n", size_text(stored), dedup_hits));
+    print(STDOUT, &format!("volume:     {} of {} used\n", size_text(used), size_text(capacity)));
+    print(STDOUT, &format!("evicted:    {} dumps\n", evicted));
+
+    let (max_dumps, per_component) = (read_u32(&record, 56), read_u32(&record, 60));
+    let (max_bytes, max_age) = (read_u64(&record, 64), read_u64(&record, 72));
+    let bytes = if max_bytes == 0 { String::from("any size") } else { size_text(max_bytes) };
+    let age = if max_age == 0 { String::from("any age") } else { format!("{} days", max_age / NS_PER_DAY) };
+    print(STDOUT, &format!("retention:  {} dumps, {} per component, {}, {}\n", max_dumps, per_component, bytes, age));
+    Ok(())
+}
+
+fn list(channel: &mut IpcChannel, component: &str) -> Result<(), String> {
+    let mut request = OP_LIST.to_le_bytes().to_vec();
+    request.extend_from_slice(&0u64.to_le_bytes());
+    request.extend_from_slice(&(component.len() as u32).to_le_bytes());
+    request.extend_from_slice(component.as_bytes());
+    let records = call(channel, &request).map_err(describe)?;
+
+    print(
+        STDOUT,
+        &format!("{:>5}  {:<19}  {:<16} {:>6} {:>9}  REASON\n", "ID", "TIME (UTC)", "COMPONENT", "PID", "SIZE"),
+    );
+    let mut offset = 0;
+    while offset + 28 <= records.len() {
+        let (id, time, pid, size) = (
+            read_u32(&records, offset),
+            read_u64(&records, offset + 4),
+            read_u64(&records, offset + 12),
+            read_u64(&records, offset + 20),
+        );
+        let Some((component, next)) = read_string(&records, offset + 28) else {
+            break;
+        };
+        let Some((reason, next)) = read_string(&records, next) else {
+            break;
+        };
+        let pid = if pid == 0 { String::from("-") } else { format!("{}", pid) };
+        print(
+            STDOUT,
+            &format!(
+                "{:>5}  {}  {:<16} {:>6} {:>9}  {}\n",
+                id,
+                time_text(time),
+                component,
+                pid,
+                size_text(size),
+                reason
+            ),
+        );
+        offset = next;
+    }
+    Ok(())
+}
+
+fn parse_id(text: &str) -> Result<u32, String> {
+    text.parse().map_err(|_| format!("{}: not a dump id", text))
+}
+
+/// Copy dump `id` into the file at `path`
+fn get(channel: &mut IpcChannel, id: u32, path: &str) -> Result<(), String> {
+    let fd = open(path, O_WRONLY | O_CREAT | O_TRUNC).map_err(|status| format!("{}: error {}", path, status))?;
+    let mut offset = 0u64;
+    let result = loop {
+        let mut request = OP_READ.to_le_bytes().to_vec();
+        request.extend_from_slice(&id.to_le_bytes());
+        request.extend_from_slice(&offset.to_le_bytes());
+        request.extend_from_slice(&MAX_READ.to_le_bytes());
+        let data = match call(channel, &request) {
+            Ok(data) if data.is_empty() => break Ok(()),
+            Ok(data) => data,
+            Err(status) => break Err(describe(status)),
+        };
+        if let Err(status) = write(fd, &data) {
+            break Err(format!("{}: error {}", path, status));
+        }
+        offset += data.len() as u64;
+    };
+    let _ = close(fd);
+    result?;
+    print(STDOUT, &format!("dump {} written to {} ({})\n", id, path, size_text(offset)));
+    Ok(())
+}
+
+fn delete(channel: &mut IpcChannel, id: u32) -> Result<(), String> {
+    let mut request = OP_DELETE.to_le_bytes().to_vec();
+    request.extend_from_slice(&id.to_le_bytes());
+    call(channel, &request).map(|_| ()).map_err(describe)
+}
+
+fn retention(channel: &mut IpcChannel, limits: &[&str]) -> Result<(), String> {
+    let number = |text: &str| text.parse::<u64>().map_err(|_| format!("{}: not a number", text));
+    let (dumps, per_component) = (number(limits[0])?, number(limits[1])?);
+    let (bytes, days) = (number(limits[2])?, number(limits[3])?);
+    if dumps == 0 || per_component == 0 || dumps > u32::MAX as u64 || per_component > u32::MAX as u64 {
+        return Err(String::from("the dump limits must be between 1 and 4294967295"));
+    }
+    let mut request = OP_SET_RETENTION.to_le_bytes().to_vec();
+    request.extend_from_slice(&(dumps as u32).to_le_bytes());
+    request.extend_from_slice(&(per_component as u32).to_le_bytes());
+    request.extend_from_slice(&bytes.to_le_bytes());
+    request.extend_from_slice(&days.saturating_mul(NS_PER_DAY).to_le_bytes());
+    call(channel, &request).map(|_| ()).map_err(describe)
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let mut channel = IpcChannel::connect("crashd");
+    let result = match args {
+        [_, "crash", "status"] => status(&mut channel),
+        [_, "crash", "list"] => list(&mut channel, ""),
+        [_, "crash", "list", component] => list(&mut channel, component),
+        [_, "crash", "get", id, path] => parse_id(id).and_then(|id| get(&mut channel, id, path)),
+        [_, "crash", "delete", id] => parse_id(id).and_then(|id| delete(&mut channel, id)),
+        [_, "crash", "retention", limits @ ..] if limits.len() == 4 => retention(&mut channel, limits),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(message) => {
+            print(STDERR, &format!("orion-storagectl: {}\n", message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
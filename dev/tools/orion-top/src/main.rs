@@ -1,7 +1,16 @@
 /*
  * Orion Operating System - Top Tool
  *
- * System monitoring and process listing tool for Orion OS.
+ * System monitoring tool for Orion OS. It watches the display drivers
+ * (see lib/orion_frameperf):
+ *
+ *   orion-top [-n count] [-d seconds] [driver...]
+ *
+ * Every `-d` seconds (1 by default), `count` times or for ever, it prints
+ * for each driver named (virtio-gpu when none is) the frame rate, the
+ * frame-time percentiles against the target, the janks (frames over twice
+ * the target) counted and the GPU utilization estimated from its command
+ * queue, then the janks that happened since the previous refresh.
  *
  * Developed by Jeremy Noverraz (1988-2025)
  * August 2025, Lausanne, Switzerland
@@ -13,8 +22,207 @@
 #![no_std]
 #![no_main]
 
-fn main() {
-    // TODO: Implement system monitoring tool
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_frameperf::{FrameClient, FrameReport, Transport};
+use orion_ipc::IpcChannel;
+use orion_sys::{nanosleep, write};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const DEFAULT_DRIVER: &str = "virtio-gpu";
+const DEFAULT_INTERVAL_S: u64 = 1;
+const JANK_BATCH: u32 = 64;
+
+const USAGE: &str = "usage: orion-top [-n count] [-d seconds] [driver...]\n";
+
+struct DriverIpc(IpcChannel);
+
+impl Transport for DriverIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+/// Nanoseconds as milliseconds with two decimals
+fn ms(ns: u64) -> String {
+    format!("{}.{:02}", ns / 1_000_000, ns / 10_000 % 100)
+}
+
+/// A display driver being watched and the last jank it printed
+struct Display {
+    name: String,
+    client: FrameClient<DriverIpc>,
+    last_jank: u64,
+}
+
+fn print_report(name: &str, report: &FrameReport) {
+    let fps = report.fps_milli();
+    let percentiles = &report.percentiles;
+    let busy = report.utilization.busy_ppm;
+    print(
+        STDOUT,
+        &format!(
+            "{:<12} {:>4}.{} fps  target {} ms  p50 {}  p90 {}  p99 {}  max {} ms\n",
+            name,
+            fps / 1000,
+            fps / 100 % 10,
+            ms(report.target_ns),
+            ms(percentiles.p50_ns),
+            ms(percentiles.p90_ns),
+            ms(percentiles.p99_ns),
+            ms(percentiles.max_ns),
+        ),
+    );
+    print(
+        STDOUT,
+        &format!(
+            "{:<12} {} frames  {} janks  gpu {}.{}% busy  queue {} (mean {}.{:02})\n",
+            "",
+            report.frames,
+            report.janks,
+            busy / 10_000,
+            busy / 1000 % 10,
+            report.queue_depth,
+            report.utilization.mean_depth_milli / 1000,
+            report.utilization.mean_depth_milli / 10 % 100,
+        ),
+    );
+}
+
+/// Print a driver's report and its new janks
+fn refresh(display: &mut Display) -> Result<(), i32> {
+    let report = display.client.query()?;
+    // Counting starts again after a reset
+    if report.frames < display.last_jank {
+        display.last_jank = 0;
+    }
+    print_report(&display.name, &report);
+    let target = report.target_ns.max(1);
+    loop {
+        let janks = display.client.janks(display.last_jank, JANK_BATCH)?;
+        let Some(last) = janks.last() else {
+            return Ok(());
+        };
+        display.last_jank = last.frame;
+        for jank in &janks {
+            let tenths = jank.duration_ns * 10 / target;
+            print(
+                STDOUT,
+                &format!(
+                    "{:<12} jank at {:>6}.{:03} s  frame {}  {} ms ({}.{}x target)\n",
+                    "",
+                    jank.time_ns / 1_000_000_000,
+                    jank.time_ns / 1_000_000 % 1000,
+                    jank.frame,
+                    ms(jank.duration_ns),
+                    tenths / 10,
+                    tenths % 10,
+                ),
+            );
+        }
+    }
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let mut count: Option<u64> = None;
+    let mut interval = DEFAULT_INTERVAL_S;
+    let mut drivers = Vec::new();
+    let mut rest = args.iter().skip(1);
+    while let Some(&arg) = rest.next() {
+        let parsed = match arg {
+            "-n" => rest.next().and_then(|value| value.parse().ok()).map(|value| count = Some(value)),
+            "-d" => rest.next().and_then(|value| value.parse().ok()).filter(|&value| value > 0).map(|value| {
+                interval = value;
+            }),
+            _ if arg.starts_with('-') => None,
+            _ => {
+                drivers.push(arg);
+                Some(())
+            }
+        };
+        if parsed.is_none() {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    }
+    if drivers.is_empty() {
+        drivers.push(DEFAULT_DRIVER);
+    }
+
+    let mut displays: Vec<Display> = drivers
+        .iter()
+        .map(|&name| Display {
+            name: String::from(name),
+            client: FrameClient::new(DriverIpc(IpcChannel::connect(name))),
+            last_jank: 0,
+        })
+        .collect();
+
+    let mut round = 0;
+    loop {
+        let mut failed = 0;
+        for display in &mut displays {
+            if let Err(status) = refresh(display) {
+                failed += 1;
+                print(
+                    STDERR,
+                    &format!(
+                        "orion-top: {}: error {}, or the driver keeps no frame statistics\n",
+                        display.name, status
+                    ),
+                );
+            }
+        }
+        if failed == displays.len() {
+            return EXIT_FAILURE;
+        }
+        round += 1;
+        if count.is_some_and(|count| round >= count) {
+            return EXIT_OK;
+        }
+        print(STDOUT, "\n");
+        let _ = nanosleep(interval * 1_000_000_000);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
 }
 
 #[panic_handler]
@@ -1,7 +1,22 @@
 /*
  * Orion Operating System - Trace Tool
  *
- * System tracing and debugging tool for Orion OS.
+ * Front end of the driver probes (see lib/orion_probe):
+ *
+ *   orion-trace points
+ *   orion-trace status <driver>
+ *   orion-trace enable <driver> <points> [rate]
+ *   orion-trace disable <driver> [points]
+ *   orion-trace dump <driver>
+ *   orion-trace follow <driver>
+ *
+ * `points` lists the trait methods a driver can probe. `points` arguments
+ * are comma separated: "all", a trait ("BlockDriver"), a method
+ * ("read_blocks") or both ("BlockDriver::read_blocks"). `enable` turns
+ * them on with at most `rate` calls traced per second (1000 by default,
+ * 0 for no limit); `disable` turns them off, all of them without an
+ * argument. `dump` prints the records in the driver's trace ring and
+ * `follow` keeps printing new ones as they come.
  *
  * Developed by Jeremy Noverraz (1988-2025)
  * August 2025, Lausanne, Switzerland
@@ -13,8 +28,221 @@
 #![no_std]
 #![no_main]
 
-fn main() {
-    // TODO: Implement system tracing tool
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+use orion_probe::{mask_of, ProbeClient, ProbeRecord, RecordKind, Transport, POINTS};
+use orion_sys::{nanosleep, write};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const STATUS_EBUSY: i32 = -16;
+const STATUS_EINVAL: i32 = -22;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const DEFAULT_RATE: u32 = 1000;
+const FOLLOW_INTERVAL_NS: u64 = 100 * 1_000_000;
+const READ_BATCH: u32 = 64;
+
+const USAGE: &str = "\
+usage: orion-trace points
+       orion-trace status <driver>
+       orion-trace enable <driver> <points> [rate]
+       orion-trace disable <driver> [points]
+       orion-trace dump <driver>
+       orion-trace follow <driver>
+";
+
+struct DriverIpc(IpcChannel);
+
+impl Transport for DriverIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EBUSY => String::from("the trace ring is busy, try again"),
+        STATUS_EINVAL => String::from("the driver does not know these probe points"),
+        status => format!("error {}, or the driver has no probes", status),
+    }
+}
+
+fn point_name(record: &ProbeRecord) -> String {
+    match record.point.info() {
+        Some(info) => format!("{}::{}", info.trait_name, info.method),
+        None => format!("point {}", record.point.0),
+    }
+}
+
+/// Names of the points in `mask`
+fn point_names(mask: u32) -> String {
+    let names: Vec<String> = POINTS
+        .iter()
+        .filter(|info| mask & info.point.bit() != 0)
+        .map(|info| format!("{}::{}", info.trait_name, info.method))
+        .collect();
+    if names.is_empty() {
+        String::from("none")
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Mask of comma separated point patterns, None when one matches nothing
+fn parse_points(patterns: &str) -> Option<u32> {
+    patterns.split(',').try_fold(0, |mask, pattern| match mask_of(pattern) {
+        0 => None,
+        bits => Some(mask | bits),
+    })
+}
+
+fn print_record(record: &ProbeRecord) {
+    let time = format!("{:>6}.{:06}", record.time_ns / 1_000_000_000, record.time_ns / 1000 % 1_000_000);
+    let detail = match record.kind {
+        RecordKind::Entry => {
+            let args: Vec<String> =
+                record.args[..record.argc as usize].iter().map(|arg| format!("{:#x}", arg)).collect();
+            format!("-> ({})", args.join(", "))
+        }
+        RecordKind::Exit => format!("<- {} in {} us", record.args[0], record.args[1] / 1000),
+        RecordKind::Error => format!("<- {} in {} us", record.error.as_str(), record.args[1] / 1000),
+    };
+    print(STDOUT, &format!("{} {:>8}  {}  {}\n", time, record.sequence, point_name(record), detail));
+}
+
+fn list_points() {
+    for info in POINTS {
+        print(STDOUT, &format!("{:>3}  {}::{}\n", info.point.0, info.trait_name, info.method));
+    }
+}
+
+fn status(client: &mut ProbeClient<DriverIpc>) -> Result<(), String> {
+    let status = client.query().map_err(describe)?;
+    let rate = if status.rate == 0 { String::from("no limit") } else { format!("{} calls/s", status.rate) };
+    print(STDOUT, &format!("enabled:  {}\n", point_names(status.enabled)));
+    print(STDOUT, &format!("rate:     {}\n", rate));
+    print(STDOUT, &format!("records:  {}\n", status.next_sequence.saturating_sub(1)));
+    print(STDOUT, &format!("limited:  {} calls\n", status.limited));
+    print(STDOUT, &format!("dropped:  {} records\n", status.dropped));
+    Ok(())
+}
+
+/// Print the records from sequence number `from` on, returning the
+/// sequence number to go on from
+fn dump(client: &mut ProbeClient<DriverIpc>, mut from: u64) -> Result<u64, String> {
+    loop {
+        let batch = client.read(from, READ_BATCH).map_err(describe)?;
+        if batch.lost > 0 {
+            print(STDOUT, &format!("... {} records overwritten\n", batch.lost));
+        }
+        from += batch.lost;
+        let Some(last) = batch.records.last() else {
+            return Ok(from);
+        };
+        from = last.sequence + 1;
+        batch.records.iter().for_each(print_record);
+    }
+}
+
+fn follow(client: &mut ProbeClient<DriverIpc>) -> Result<(), String> {
+    // Only what comes from now on
+    let mut from = client.query().map_err(describe)?.next_sequence;
+    loop {
+        from = dump(client, from)?;
+        let _ = nanosleep(FOLLOW_INTERVAL_NS);
+    }
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let (command, driver, rest) = match args {
+        [_, "points"] => {
+            list_points();
+            return EXIT_OK;
+        }
+        [_, command, driver, rest @ ..] => (*command, *driver, rest),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    let mut client = ProbeClient::new(DriverIpc(IpcChannel::connect(driver)));
+
+    let result = match (command, rest) {
+        ("status", []) => status(&mut client),
+        ("enable", [points]) | ("enable", [points, _]) => {
+            let rate = match rest.get(1).map(|rate| rate.parse::<u32>()) {
+                None => Ok(DEFAULT_RATE),
+                Some(rate) => rate.map_err(|_| ()),
+            };
+            match (parse_points(points), rate) {
+                (Some(mask), Ok(rate)) => client.enable(mask, rate).map_err(describe),
+                (None, _) => Err(format!("{}: no such probe point (see orion-trace points)", points)),
+                (_, Err(())) => {
+                    print(STDERR, USAGE);
+                    return EXIT_USAGE;
+                }
+            }
+        }
+        ("disable", []) => client.disable(u32::MAX).map_err(describe),
+        ("disable", [points]) => match parse_points(points) {
+            Some(mask) => client.disable(mask).map_err(describe),
+            None => Err(format!("{}: no such probe point (see orion-trace points)", points)),
+        },
+        ("dump", []) => dump(&mut client, 1).map(|_| ()),
+        ("follow", []) => follow(&mut client),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(message) => {
+            print(STDERR, &format!("orion-trace: {}: {}\n", driver, message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
 }
 
 #[panic_handler]
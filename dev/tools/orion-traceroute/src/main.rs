@@ -0,0 +1,551 @@
+/*
+ * Orion Operating System - Traceroute Tool
+ *
+ * Prints the routers on the way to a host, or the path MTU to it:
+ *
+ *   orion-traceroute [-I] [-f first_ttl] [-m max_hops] [-q probes]
+ *                    [-w wait] [-p port] <address>
+ *   orion-traceroute --mtu [-I] [-M max] [-q probes] [-w wait]
+ *                    [-p port] <address>
+ *
+ * Tracing sends `probes` packets (default 3) per TTL, starting at
+ * `first_ttl` (default 1) up to `max_hops` (default 30), and prints who
+ * answered each and how fast, `*` when nothing did within `wait` seconds
+ * (default 3). Probes are UDP datagrams to `port` (default 33434) plus
+ * the probe number, which the destination answers with port
+ * unreachable; `-I` sends ICMP echo requests instead, which needs the
+ * raw network capability. Other unreachable answers end the trace and
+ * are flagged as !N (network), !H (host), !P (protocol), !F
+ * (fragmentation needed), !X (prohibited) or !<code>.
+ *
+ * With `--mtu` the probes carry don't-fragment and the tool searches for
+ * the largest packet up to `max` bytes (default and limit 1500) that
+ * reaches the host, following the next-hop MTU routers report and
+ * bisecting when they say nothing. A size counts as lost after `probes`
+ * probes went unanswered.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_http::socket::NetChannel;
+use orion_ipc::IpcChannel;
+use orion_ping::socket::{STATUS_EMSGSIZE, STATUS_EPERM};
+use orion_ping::{EchoKind, EchoSocket, Family, Hop, PmtuSearch, ProbeAnswer, ProbeResult, ProbeSocket, Tracer};
+use orion_sys::{clock_get, nanosleep, write};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// Pause between two polls of the probe socket
+const POLL_INTERVAL_NS: u64 = 1_000_000;
+
+const DEFAULT_MAX_HOPS: u8 = 30;
+const DEFAULT_PROBES: usize = 3;
+const DEFAULT_WAIT_NS: u64 = 3 * NS_PER_SEC;
+const DEFAULT_PORT: u16 = 33434;
+const MAX_PROBES: usize = 10;
+
+/// Data carried by trace probes
+const TRACE_DATA_SIZE: usize = 32;
+/// IPv4 header plus the UDP or ICMP echo header
+const PROBE_OVERHEAD: u16 = 28;
+/// Largest packet the network server lets a probe carry
+const MAX_PROBE_SIZE: u16 = 1500;
+const PMTU_TTL: u8 = 64;
+
+const ICMP_CODE_FRAG_NEEDED: u8 = 4;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-traceroute [-I] [-f first_ttl] [-m max_hops] [-q probes] [-w wait] [-p port] <address>
+       orion-traceroute --mtu [-I] [-M max] [-q probes] [-w wait] [-p port] <address>
+";
+
+/// IPC channel to the network server
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+fn now_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+struct Options<'a> {
+    icmp: bool,
+    mtu: bool,
+    first_ttl: u8,
+    max_hops: u8,
+    probes: usize,
+    wait_ns: u64,
+    port: u16,
+    max_size: u16,
+    address: &'a str,
+}
+
+/// Seconds with an optional fraction ("3", "0.5") in nanoseconds
+fn parse_seconds(text: &str) -> Option<u64> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if (whole.is_empty() && fraction.is_empty()) || fraction.len() > 9 {
+        return None;
+    }
+    let seconds: u64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let mut nanos = 0u64;
+    for (index, digit) in fraction.bytes().enumerate() {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        nanos += (digit - b'0') as u64 * 10u64.pow(8 - index as u32);
+    }
+    seconds.checked_mul(NS_PER_SEC)?.checked_add(nanos)
+}
+
+fn parse_options<'a>(args: &[&'a str]) -> Option<Options<'a>> {
+    let mut options = Options {
+        icmp: false,
+        mtu: false,
+        first_ttl: 1,
+        max_hops: DEFAULT_MAX_HOPS,
+        probes: DEFAULT_PROBES,
+        wait_ns: DEFAULT_WAIT_NS,
+        port: DEFAULT_PORT,
+        max_size: MAX_PROBE_SIZE,
+        address: "",
+    };
+    let mut index = 1;
+    while index < args.len() {
+        match args[index] {
+            "-I" => options.icmp = true,
+            "--mtu" => options.mtu = true,
+            "-f" => {
+                index += 1;
+                options.first_ttl = args.get(index)?.parse().ok().filter(|ttl| *ttl > 0)?;
+            }
+            "-m" => {
+                index += 1;
+                options.max_hops = args.get(index)?.parse().ok().filter(|hops| *hops > 0)?;
+            }
+            "-q" => {
+                index += 1;
+                options.probes = args
+                    .get(index)?
+                    .parse()
+                    .ok()
+                    .filter(|probes| (1..=MAX_PROBES).contains(probes))?;
+            }
+            "-w" => {
+                index += 1;
+                options.wait_ns = parse_seconds(args.get(index)?)?.max(POLL_INTERVAL_NS);
+            }
+            "-p" => {
+                index += 1;
+                options.port = args.get(index)?.parse().ok().filter(|port| *port > 0)?;
+            }
+            "-M" => {
+                index += 1;
+                options.max_size = args
+                    .get(index)?
+                    .parse()
+                    .ok()
+                    .filter(|size| (PROBE_OVERHEAD..=MAX_PROBE_SIZE).contains(size))?;
+            }
+            address if options.address.is_empty() && !address.starts_with('-') => options.address = address,
+            _ => return None,
+        }
+        index += 1;
+    }
+    if options.first_ttl > options.max_hops {
+        return None;
+    }
+    (!options.address.is_empty()).then_some(options)
+}
+
+fn parse_ipv4(text: &str) -> Option<u32> {
+    let mut octets = [0u8; 4];
+    let mut parts = text.split('.');
+    for octet in octets.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        *octet = part.parse().ok()?;
+    }
+    parts.next().is_none().then(|| u32::from_be_bytes(octets))
+}
+
+fn format_ipv4(address: u32) -> String {
+    let [a, b, c, d] = address.to_be_bytes();
+    format!("{}.{}.{}.{}", a, b, c, d)
+}
+
+/// Milliseconds with three decimals
+fn format_ms(ns: u64) -> String {
+    format!("{}.{:03}", ns / 1_000_000, ns % 1_000_000 / 1000)
+}
+
+/// Flag printed after a probe that drew an unreachable other than port
+fn unreachable_flag(code: u8) -> String {
+    match code {
+        0 => String::from("!N"),
+        1 => String::from("!H"),
+        2 => String::from("!P"),
+        ICMP_CODE_FRAG_NEEDED => String::from("!F"),
+        13 => String::from("!X"),
+        code => format!("!{}", code),
+    }
+}
+
+/// One line per hop; the address is repeated only when it changes
+fn format_hop(hop: &Hop) -> String {
+    let mut line = format!("{:2}", hop.ttl);
+    let mut last_from = None;
+    for result in &hop.probes {
+        match *result {
+            ProbeResult::TimedOut => line.push_str("  *"),
+            ProbeResult::Answered { from, rtt_ns, answer } => {
+                if last_from != Some(from) {
+                    line.push_str(&format!("  {}", format_ipv4(from)));
+                    last_from = Some(from);
+                }
+                line.push_str(&format!("  {} ms", format_ms(rtt_ns)));
+                if let ProbeAnswer::Unreachable(code) = answer {
+                    line.push(' ');
+                    line.push_str(&unreachable_flag(code));
+                }
+            }
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// Something that came back for a probe
+struct Answer {
+    key: u16,
+    from: u32,
+    kind: EchoKind,
+    mtu: u16,
+}
+
+/// Probes go out as UDP datagrams or ICMP echo requests; the probe key is
+/// the offset of the destination port or the echo sequence number
+enum Prober {
+    Udp {
+        socket: ProbeSocket<NetIpc>,
+        port: u16,
+        ttl: u8,
+    },
+    Icmp(EchoSocket<NetIpc>),
+}
+
+impl Prober {
+    fn open(icmp: bool, port: u16) -> Result<Self, i32> {
+        let channel = NetIpc(IpcChannel::connect("net"));
+        if icmp {
+            return EchoSocket::open(channel, Family::Inet).map(Prober::Icmp);
+        }
+        let socket = ProbeSocket::bind(channel, 0)?;
+        Ok(Prober::Udp { socket, port, ttl: 0 })
+    }
+
+    fn set_dont_fragment(&mut self, dont_fragment: bool) -> Result<(), i32> {
+        match self {
+            Prober::Udp { socket, .. } => socket.set_dont_fragment(dont_fragment),
+            Prober::Icmp(socket) => {
+                socket.set_dont_fragment(dont_fragment);
+                Ok(())
+            }
+        }
+    }
+
+    fn send(&mut self, destination: u32, key: u16, ttl: u8, data: &[u8]) -> Result<(), i32> {
+        match self {
+            Prober::Udp {
+                socket,
+                port,
+                ttl: current,
+            } => {
+                if *current != ttl {
+                    socket.set_ttl(ttl)?;
+                    *current = ttl;
+                }
+                socket.send_to(destination, port.wrapping_add(key), data)
+            }
+            Prober::Icmp(socket) => socket.send(destination, key, ttl, data),
+        }
+    }
+
+    /// Next answer to a probe sent to `destination`, None while there is none
+    fn poll(&mut self, destination: u32) -> Result<Option<Answer>, i32> {
+        loop {
+            match self {
+                Prober::Udp { socket, port, .. } => {
+                    let Some(error) = socket.recv_error()? else {
+                        return Ok(None);
+                    };
+                    if error.destination != destination {
+                        continue;
+                    }
+                    return Ok(Some(Answer {
+                        key: error.port.wrapping_sub(*port),
+                        from: error.reporter,
+                        kind: error.kind,
+                        mtu: error.mtu,
+                    }));
+                }
+                Prober::Icmp(socket) => {
+                    return Ok(socket.recv()?.map(|message| Answer {
+                        key: message.sequence,
+                        from: message.source,
+                        kind: message.kind,
+                        mtu: message.mtu,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+fn trace(prober: &mut Prober, destination: u32, options: &Options) -> i32 {
+    print(
+        STDOUT,
+        &format!(
+            "traceroute to {}, {} hops max, {} byte packets\n",
+            options.address,
+            options.max_hops,
+            TRACE_DATA_SIZE as u16 + PROBE_OVERHEAD
+        ),
+    );
+    let data = [0u8; TRACE_DATA_SIZE];
+    let mut tracer = Tracer::new(options.first_ttl, options.max_hops, options.probes);
+    let mut reached = false;
+
+    while !tracer.finished() {
+        let ttl = tracer.ttl();
+        while let Some(key) = tracer.next_probe(now_ns()) {
+            if let Err(status) = prober.send(destination, key, ttl, &data) {
+                print(STDERR, &format!("orion-traceroute: send failed ({})\n", status));
+                return EXIT_FAILURE;
+            }
+        }
+        while !tracer.hop_complete() {
+            while let Ok(Some(answer)) = prober.poll(destination) {
+                if let Some(kind) = ProbeAnswer::from_kind(answer.kind) {
+                    tracer.answer(answer.key, answer.from, kind, now_ns());
+                }
+            }
+            tracer.expire(now_ns(), options.wait_ns);
+            let _ = nanosleep(POLL_INTERVAL_NS);
+        }
+        let hop = tracer.finish_hop();
+        reached |= hop.probes.iter().any(|result| {
+            matches!(
+                result,
+                ProbeResult::Answered {
+                    answer: ProbeAnswer::Reached,
+                    ..
+                }
+            )
+        });
+        print(STDOUT, &format_hop(&hop));
+    }
+    if reached {
+        EXIT_OK
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+enum ProbeOutcome {
+    Passed,
+    TooBig { from: u32, mtu: u16 },
+    Lost,
+}
+
+/// Send probes of `size` bytes until one is answered or `options.probes`
+/// went unanswered
+fn probe_size(prober: &mut Prober, destination: u32, size: u16, key: &mut u16, options: &Options) -> ProbeOutcome {
+    let data = vec![0u8; (size - PROBE_OVERHEAD) as usize];
+    for _ in 0..options.probes {
+        let sent_key = *key;
+        *key = key.wrapping_add(1);
+        match prober.send(destination, sent_key, PMTU_TTL, &data) {
+            Ok(()) => {}
+            // Larger than the MTU of our own route
+            Err(STATUS_EMSGSIZE) => return ProbeOutcome::TooBig { from: 0, mtu: 0 },
+            Err(_) => return ProbeOutcome::Lost,
+        }
+        let sent_at = now_ns();
+        while now_ns() - sent_at < options.wait_ns {
+            while let Ok(Some(answer)) = prober.poll(destination) {
+                if answer.key != sent_key {
+                    continue;
+                }
+                match answer.kind {
+                    EchoKind::Reply | EchoKind::Unreachable(3) => return ProbeOutcome::Passed,
+                    EchoKind::Unreachable(ICMP_CODE_FRAG_NEEDED) => {
+                        return ProbeOutcome::TooBig {
+                            from: answer.from,
+                            mtu: answer.mtu,
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let _ = nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+    ProbeOutcome::Lost
+}
+
+fn discover_mtu(prober: &mut Prober, destination: u32, options: &Options) -> i32 {
+    if let Err(status) = prober.set_dont_fragment(true) {
+        print(
+            STDERR,
+            &format!("orion-traceroute: cannot set don't-fragment ({})\n", status),
+        );
+        return EXIT_FAILURE;
+    }
+    let mut search = PmtuSearch::new(options.max_size);
+    let mut key = 0u16;
+    let mut confirmed = false;
+
+    while let Some(size) = search.next_size() {
+        let line = match probe_size(prober, destination, size, &mut key, options) {
+            ProbeOutcome::Passed => {
+                search.passed(size);
+                confirmed = true;
+                format!("{} bytes: ok\n", size)
+            }
+            ProbeOutcome::TooBig { from: 0, .. } => {
+                search.too_big(size, 0);
+                format!("{} bytes: too big for the local route\n", size)
+            }
+            ProbeOutcome::TooBig { from, mtu } => {
+                search.too_big(size, mtu);
+                if mtu == 0 {
+                    format!("{} bytes: fragmentation needed at {}\n", size, format_ipv4(from))
+                } else {
+                    format!(
+                        "{} bytes: fragmentation needed at {} (next hop MTU {})\n",
+                        size,
+                        format_ipv4(from),
+                        mtu
+                    )
+                }
+            }
+            ProbeOutcome::Lost => {
+                search.lost(size);
+                format!("{} bytes: no answer\n", size)
+            }
+        };
+        print(STDOUT, &line);
+    }
+
+    if !confirmed {
+        print(
+            STDERR,
+            &format!("orion-traceroute: {} did not answer any probe\n", options.address),
+        );
+        return EXIT_FAILURE;
+    }
+    print(STDOUT, &format!("path MTU to {}: {}\n", options.address, search.mtu()));
+    EXIT_OK
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let Some(options) = parse_options(args) else {
+        print(STDERR, USAGE);
+        return EXIT_USAGE;
+    };
+    let Some(destination) = parse_ipv4(options.address) else {
+        print(
+            STDERR,
+            &format!("orion-traceroute: {}: not an IPv4 address\n", options.address),
+        );
+        return EXIT_USAGE;
+    };
+
+    let mut prober = match Prober::open(options.icmp, options.port) {
+        Ok(prober) => prober,
+        Err(STATUS_EPERM) => {
+            print(STDERR, "orion-traceroute: -I needs the raw network capability\n");
+            return EXIT_USAGE;
+        }
+        Err(status) => {
+            print(
+                STDERR,
+                &format!("orion-traceroute: cannot open a probe socket ({})\n", status),
+            );
+            return EXIT_FAILURE;
+        }
+    };
+
+    if options.mtu {
+        discover_mtu(&mut prober, destination, &options)
+    } else {
+        trace(&mut prober, destination, &options)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
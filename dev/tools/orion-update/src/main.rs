@@ -0,0 +1,322 @@
+/*
+ * Orion Operating System - System Update Tool
+ *
+ * Front end of the update server:
+ *
+ *   orion-update status
+ *   orion-update apply <image> [tries]
+ *   orion-update abort
+ *   orion-update confirm
+ *
+ * `status` shows the two system slots, the slot running and whether its
+ * boot still waits to be confirmed. `apply` sends a system image to the
+ * server, which writes it to the slot not running and has the next start
+ * boot it with `tries` attempts (1 by default) to complete a boot. `abort`
+ * drops an update still being sent. `confirm` is run by init once the
+ * boot completed; it also confirms the configuration version on trial
+ * (see services/config), so both stay after the next start.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_install::bootctl::{SLOT_BOOTABLE, SLOT_SUCCESSFUL};
+use orion_install::Guid;
+use orion_ipc::IpcChannel;
+use orion_sys::{close, open, read, write, O_RDONLY};
+
+// Global allocator for the tool
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+const STDOUT: u64 = 1;
+const STDERR: u64 = 2;
+
+// Update server requests (see services/update/src/protocol.rs)
+const OP_STATUS: u32 = 1;
+const OP_BEGIN: u32 = 2;
+const OP_WRITE: u32 = 3;
+const OP_COMMIT: u32 = 4;
+const OP_ABORT: u32 = 5;
+const OP_CONFIRM: u32 = 6;
+const MAX_WRITE: usize = 60 * 1024;
+
+const BOOT_PENDING: u32 = 1;
+const BOOT_CONFIRMED: u32 = 2;
+const BOOT_FAILED: u32 = 3;
+const UPDATE_RECEIVING: u32 = 1;
+const UPDATE_PENDING_REBOOT: u32 = 2;
+
+const CONFIG_OP_CONFIRM: u32 = 5;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EBUSY: i32 = -16;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOSPC: i32 = -28;
+const STATUS_ETIMEDOUT: i32 = -110;
+const STATUS_EKEYREJECTED: i32 = -129;
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+usage: orion-update status
+       orion-update apply <image> [tries]
+       orion-update abort
+       orion-update confirm
+";
+
+const SLOT_NAMES: [&str; 2] = ["A", "B"];
+
+fn print(fd: u64, text: &str) {
+    let _ = write(fd, text.as_bytes());
+}
+
+/// Send a request to `channel`, returning the reply payload or the status
+fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = channel.call(request).map_err(|_| STATUS_ENOENT)?;
+    if response.len() < 4 {
+        return Err(STATUS_ENOENT);
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn describe(status: i32) -> String {
+    match status {
+        STATUS_EPERM => String::from("permission denied"),
+        STATUS_ENOENT => String::from("no update server, or the system does not run from an installed disk"),
+        STATUS_EBUSY => String::from("an update is running, or the current boot is not confirmed yet"),
+        STATUS_EINVAL => String::from("not a sealed system image, or not of the size it describes"),
+        STATUS_ENOSPC => String::from("the image does not fit the system slot"),
+        STATUS_ETIMEDOUT => String::from("too late, the next start returns to the previous system"),
+        STATUS_EKEYREJECTED => String::from("the image is not signed by a trusted key"),
+        status => format!("error {}", status),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8).map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn status(channel: &mut IpcChannel) -> Result<(), String> {
+    let record = call(channel, &OP_STATUS.to_le_bytes()).map_err(describe)?;
+    if record.len() < 20 + 2 * 24 + 20 {
+        return Err(String::from("short status reply"));
+    }
+    let booted = read_u32(&record, 0) as usize & 1;
+    let active = read_u32(&record, 4) as usize & 1;
+    let boot = match read_u32(&record, 8) {
+        BOOT_PENDING => format!("on trial, {} s left to confirm", read_u64(&record, 12) / 1000),
+        BOOT_CONFIRMED => String::from("confirmed"),
+        BOOT_FAILED => String::from("not confirmed in time"),
+        _ => String::from("good"),
+    };
+    print(STDOUT, &format!("running:   slot {} ({})\n", SLOT_NAMES[booted], boot));
+    print(STDOUT, &format!("next boot: slot {}\n", SLOT_NAMES[active]));
+
+    for (index, name) in SLOT_NAMES.iter().enumerate() {
+        let base = 20 + index * 24;
+        let (flags, tries) = (read_u32(&record, base), read_u32(&record, base + 4));
+        let mut guid = [0u8; 16];
+        guid.copy_from_slice(&record[base + 8..base + 24]);
+        let state = if flags & SLOT_BOOTABLE == 0 {
+            String::from("not bootable")
+        } else if flags & SLOT_SUCCESSFUL != 0 {
+            String::from("bootable")
+        } else {
+            format!("bootable, {} tries left", tries)
+        };
+        print(STDOUT, &format!("slot {}:    {}  {}\n", name, Guid(guid), state));
+    }
+
+    let base = 20 + 2 * 24;
+    match read_u32(&record, base) {
+        UPDATE_RECEIVING => print(
+            STDOUT,
+            &format!("update:    receiving, {} of {} bytes\n", read_u64(&record, base + 4), read_u64(&record, base + 12)),
+        ),
+        UPDATE_PENDING_REBOOT => print(STDOUT, "update:    installed, restart to boot it\n"),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Size of the file at `path`; without a way to ask for it the file is
+/// read through
+fn file_size(path: &str) -> Result<u64, i32> {
+    let fd = open(path, O_RDONLY)?;
+    let mut chunk = [0u8; 4096];
+    let mut size = 0u64;
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Ok(size),
+            Ok(count) => size += count as u64,
+            Err(status) => break Err(status),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+/// Fill `buffer` from `fd`, short only at the end of the file
+fn read_full(fd: u64, buffer: &mut [u8]) -> Result<usize, i32> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match read(fd, &mut buffer[filled..])? {
+            0 => break,
+            count => filled += count,
+        }
+    }
+    Ok(filled)
+}
+
+fn send_image(channel: &mut IpcChannel, path: &str, size: u64) -> Result<(), String> {
+    let fd = open(path, O_RDONLY).map_err(|status| format!("{}: error {}", path, status))?;
+    let mut chunk = alloc::vec![0u8; MAX_WRITE];
+    let mut offset = 0u64;
+    let mut percent = u64::MAX;
+    let result = loop {
+        let count = match read_full(fd, &mut chunk) {
+            Ok(0) => break Ok(()),
+            Ok(count) => count,
+            Err(status) => break Err(format!("{}: error {}", path, status)),
+        };
+        let mut request = OP_WRITE.to_le_bytes().to_vec();
+        request.extend_from_slice(&offset.to_le_bytes());
+        request.extend_from_slice(&(count as u32).to_le_bytes());
+        request.extend_from_slice(&chunk[..count]);
+        if let Err(status) = call(channel, &request) {
+            break Err(describe(status));
+        }
+        offset += count as u64;
+        let done = (offset * 100).checked_div(size).unwrap_or(100);
+        if done != percent {
+            percent = done;
+            print(STDOUT, &format!("\rsending the image {:>3}%", percent));
+        }
+    };
+    let _ = close(fd);
+    print(STDOUT, "\n");
+    result?;
+    if offset != size {
+        return Err(format!("{}: changed while being sent", path));
+    }
+    Ok(())
+}
+
+fn apply(channel: &mut IpcChannel, path: &str, tries: u32) -> Result<(), String> {
+    let size = file_size(path).map_err(|status| format!("{}: error {}", path, status))?;
+    let mut request = OP_BEGIN.to_le_bytes().to_vec();
+    request.extend_from_slice(&size.to_le_bytes());
+    let slot = call(channel, &request).map_err(describe)?;
+    print(STDOUT, &format!("writing to slot {}\n", SLOT_NAMES[read_u32(&slot, 0) as usize & 1]));
+
+    if let Err(message) = send_image(channel, path, size) {
+        let _ = call(channel, &OP_ABORT.to_le_bytes());
+        return Err(message);
+    }
+
+    // The server reads the whole slot back before answering
+    print(STDOUT, "verifying\n");
+    let mut request = OP_COMMIT.to_le_bytes().to_vec();
+    request.extend_from_slice(&tries.to_le_bytes());
+    call(channel, &request).map_err(describe)?;
+    print(STDOUT, "update installed, restart to boot it; unless that boot is confirmed the previous system comes back\n");
+    Ok(())
+}
+
+/// Confirm the boot to the update and the configuration servers. A server
+/// with nothing on trial answers success as well
+fn confirm(channel: &mut IpcChannel) -> Result<(), String> {
+    let update = match call(channel, &OP_CONFIRM.to_le_bytes()) {
+        // Not started from an installed disk: nothing to keep
+        Ok(_) | Err(STATUS_ENOENT) => Ok(()),
+        Err(status) => Err(format!("system slot: {}", describe(status))),
+    };
+    let config = call(&mut IpcChannel::connect("config"), &CONFIG_OP_CONFIRM.to_le_bytes())
+        .map(|_| ())
+        .map_err(|status| format!("configuration: {}", describe(status)));
+    update.and(config)
+}
+
+/// Arguments as strings; argv holds NUL terminated C strings
+unsafe fn arguments(argc: i32, argv: *const *const u8) -> Vec<&'static str> {
+    (0..argc.max(0) as usize)
+        .map(|index| {
+            let start = *argv.add(index);
+            let mut length = 0;
+            while *start.add(length) != 0 {
+                length += 1;
+            }
+            core::str::from_utf8(core::slice::from_raw_parts(start, length)).unwrap_or("")
+        })
+        .collect()
+}
+
+fn run(args: &[&str]) -> i32 {
+    let mut channel = IpcChannel::connect("update");
+    let result = match args {
+        [_, "status"] => status(&mut channel),
+        [_, "apply", image] => apply(&mut channel, image, 1),
+        [_, "apply", image, tries] => match tries.parse() {
+            Ok(tries) if tries > 0 => apply(&mut channel, image, tries),
+            _ => {
+                print(STDERR, USAGE);
+                return EXIT_USAGE;
+            }
+        },
+        [_, "abort"] => call(&mut channel, &OP_ABORT.to_le_bytes()).map(|_| ()).map_err(describe),
+        [_, "confirm"] => confirm(&mut channel),
+        _ => {
+            print(STDERR, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(message) => {
+            print(STDERR, &format!("orion-update: {}\n", message));
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const u8) -> i32 {
+    let args = unsafe { arguments(argc, argv) };
+    run(&args)
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -19,7 +19,12 @@
 
 #include "../hal/common/types.h"
 #include "config.h"
+#include "arch.h"
 #include <stdio.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+
+extern void scheduler_tick(void);
 
 // ============================================================================
 // GLOBAL VARIABLES
@@ -382,89 +387,7 @@ void aarch64_print_cpu_info(void)
 // MMU MANAGEMENT
 // ============================================================================
 
-void aarch64_mmu_init(void)
-{
-    printf("Initializing aarch64 MMU...\n");
-
-    // Configure TCR_EL1 for 4KB pages and 48-bit address space
-    uint64_t tcr_el1 = 0;
-
-    // T0SZ: Top 16 bits of address space for TTBR0_EL1
-    tcr_el1 |= (16ULL << 0);
-
-    // T1SZ: Top 16 bits of address space for TTBR1_EL1
-    tcr_el1 |= (16ULL << 16);
-
-    // TG0: Granule size for TTBR0_EL1 (4KB)
-    tcr_el1 |= (0ULL << 14);
-
-    // TG1: Granule size for TTBR1_EL1 (4KB)
-    tcr_el1 |= (2ULL << 30);
-
-    // SH0: Shareability for TTBR0_EL1 (Outer Shareable)
-    tcr_el1 |= (2ULL << 12);
-
-    // SH1: Shareability for TTBR1_EL1 (Outer Shareable)
-    tcr_el1 |= (2ULL << 28);
-
-    // ORGN0: Outer cacheability for TTBR0_EL1 (Write-Back)
-    tcr_el1 |= (1ULL << 10);
-
-    // ORGN1: Outer cacheability for TTBR1_EL1 (Write-Back)
-    tcr_el1 |= (1ULL << 26);
-
-    // IRGN0: Inner cacheability for TTBR0_EL1 (Write-Back)
-    tcr_el1 |= (1ULL << 8);
-
-    // IRGN1: Inner cacheability for TTBR1_EL1 (Write-Back)
-    tcr_el1 |= (1ULL << 24);
-
-    // EPD0: Disable TTBR0_EL1 translation
-    tcr_el1 |= (0ULL << 7);
-
-    // EPD1: Disable TTBR1_EL1 translation
-    tcr_el1 |= (0ULL << 23);
-
-    // Write TCR_EL1
-    __asm__ volatile("msr TCR_EL1, %0" : : "r"(tcr_el1));
-
-    // Configure MAIR_EL1 for memory attributes
-    uint64_t mair_el1 = 0;
-
-    // Normal memory, Write-Back, Read-Allocate, Write-Allocate
-    mair_el1 |= (0xFFULL << 0);
-
-    // Device memory, nGnRE
-    mair_el1 |= (0x04ULL << 8);
-
-    // Write MAIR_EL1
-    __asm__ volatile("msr MAIR_EL1, %0" : : "r"(mair_el1));
-
-    printf("MMU initialized successfully\n");
-}
-
-int aarch64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags)
-{
-    // This is a simplified implementation
-    // In a real system, you would need to:
-    // 1. Allocate page tables if needed
-    // 2. Set up the page table entries
-    // 3. Handle different page sizes
-    // 4. Invalidate TLBs
-
-    printf("Mapping page: VA=0x%llX, PA=0x%llX, flags=0x%llX\n", va, pa, flags);
-
-    // For now, just return success
-    return 0;
-}
-
-int aarch64_mmu_unmap_page(uint64_t va)
-{
-    printf("Unmapping page: VA=0x%llX\n", va);
-
-    // For now, just return success
-    return 0;
-}
+// Page tables, aarch64_mmu_init and the page mapping calls live in mmu.c
 
 void aarch64_mmu_invalidate_tlb(void)
 {
@@ -500,54 +423,128 @@ void aarch64_interrupts_init(void)
 
 void aarch64_interrupt_enable(uint32_t irq)
 {
-    // This would interact with the GIC
-    printf("Enabling IRQ %u\n", irq);
+    irq_enable(irq);
 }
 
 void aarch64_interrupt_disable(uint32_t irq)
 {
-    // This would interact with the GIC
-    printf("Disabling IRQ %u\n", irq);
+    irq_disable(irq);
+}
+
+static void aarch64_legacy_irq(uint32_t irq, void *data)
+{
+    (void)irq;
+    ((void (*)(void))data)();
 }
 
 void aarch64_interrupt_set_handler(uint32_t irq, void (*handler)(void))
 {
-    // This would set up the interrupt handler
-    printf("Setting handler for IRQ %u\n", irq);
+    // Handlers without arguments are registered with the orion-irq layer
+    // through a trampoline
+    irq_free(irq);
+    if (handler && irq_request(irq, aarch64_legacy_irq, (void *)handler, "aarch64") != 0)
+    {
+        printf("Cannot set handler for IRQ %u\n", irq);
+    }
 }
 
 // ============================================================================
 // TIMER MANAGEMENT
 // ============================================================================
 
+// Generic timer: the EL1 physical timer, whose frequency firmware reports
+// in CNTFRQ_EL0 (62.5 MHz on QEMU virt, 24 MHz or 19.2 MHz on most boards)
+static uint64_t timer_frequency = 0;
+static uint64_t timer_period_ticks = 0;
+static uint32_t timer_irq = AARCH64_TIMER_PPI_NS_PHYS;
+
+static void aarch64_timer_tick(uint32_t irq, void *data)
+{
+    (void)irq;
+    (void)data;
+    uint64_t cval;
+    __asm__ volatile("mrs %0, cntp_cval_el0" : "=r"(cval));
+    __asm__ volatile("msr cntp_cval_el0, %0" : : "r"(cval + timer_period_ticks));
+    scheduler_tick();
+}
+
+// Arm the periodic tick on the running CPU. The timer interrupt is a PPI,
+// banked per CPU, and is unmasked here on each of them
+void aarch64_timer_cpu_init(void)
+{
+    if (!timer_period_ticks)
+    {
+        return;
+    }
+    uint64_t now;
+    __asm__ volatile("mrs %0, cntpct_el0" : "=r"(now));
+    __asm__ volatile("msr cntp_cval_el0, %0" : : "r"(now + timer_period_ticks));
+    __asm__ volatile("msr cntp_ctl_el0, %0" : : "r"(1ULL));
+    irq_enable(timer_irq);
+}
+
 void aarch64_timer_init(void)
 {
     printf("Initializing aarch64 generic timer...\n");
 
     // Read CNTFRQ_EL0 to get timer frequency
-    uint64_t cntfrq;
-    __asm__ volatile("mrs %0, CNTFRQ_EL0" : "=r"(cntfrq));
-    printf("Timer frequency: %llu Hz\n", cntfrq);
+    __asm__ volatile("mrs %0, CNTFRQ_EL0" : "=r"(timer_frequency));
+    if (timer_frequency == 0)
+    {
+        timer_frequency = AARCH64_TIMER_FREQ_DEFAULT;
+        printf("WARNING: CNTFRQ_EL0 not set, assuming %llu Hz\n", timer_frequency);
+    }
+    printf("Timer frequency: %llu Hz\n", timer_frequency);
+    timer_period_ticks = timer_frequency / AARCH64_TIMER_TICK_HZ;
+
+    // The device tree lists the secure, non-secure, virtual and hypervisor
+    // timer interrupts in that order
+    const fdt_t *fdt = fdt_boot();
+    int node = fdt ? fdt_node_offset_by_compatible(fdt, -1, "arm,armv8-timer") : -1;
+    uint32_t cells[3];
+    uint32_t type;
+    if (node >= 0 && fdt_read_interrupt(fdt, node, 1, cells, 3) == 3)
+    {
+        irq_xlate(cells, 3, &timer_irq, &type);
+    }
 
-    // Enable timer interrupts
-    __asm__ volatile("msr cntp_ctl_el0, %0" : : "r"(1ULL));
+    if (irq_request(timer_irq, aarch64_timer_tick, NULL, "arch-timer") != 0)
+    {
+        printf("ERROR: cannot attach the timer to IRQ %u\n", timer_irq);
+        return;
+    }
+    aarch64_timer_cpu_init();
+    printf("Timer initialized successfully, %u Hz tick on IRQ %u\n", AARCH64_TIMER_TICK_HZ, timer_irq);
+}
 
-    printf("Timer initialized successfully\n");
+uint64_t aarch64_timer_get_frequency(void)
+{
+    return timer_frequency;
 }
 
 uint64_t aarch64_timer_read_ns(void)
 {
     uint64_t cntpct;
     __asm__ volatile("mrs %0, CNTPCT_EL0" : "=r"(cntpct));
+    if (!timer_frequency)
+    {
+        return 0;
+    }
 
-    // Convert to nanoseconds (assuming 24MHz timer)
-    return (cntpct * 1000000000ULL) / 24000000ULL;
+    // Split to avoid overflowing the 64-bit product
+    return (cntpct / timer_frequency) * 1000000000ULL + ((cntpct % timer_frequency) * 1000000000ULL) / timer_frequency;
 }
 
 int aarch64_timer_set_oneshot(uint64_t deadline_ns)
 {
+    if (!timer_frequency)
+    {
+        return -1;
+    }
+
     // Convert nanoseconds to timer ticks
-    uint64_t ticks = (deadline_ns * 24000000ULL) / 1000000000ULL;
+    uint64_t ticks = (deadline_ns / 1000000000ULL) * timer_frequency +
+                     ((deadline_ns % 1000000000ULL) * timer_frequency) / 1000000000ULL;
 
     // Set compare value
     __asm__ volatile("msr cntp_cval_el0, %0" : : "r"(ticks));
@@ -866,10 +863,9 @@ void aarch64_arch_init(void)
     // Detect CPU features first
     aarch64_detect_cpu_features();
 
-    // Initialize subsystems
-    aarch64_mmu_init();
-    aarch64_interrupts_init();
-    aarch64_timer_init();
+    // Initialize subsystems. The MMU is already on (aarch64_boot_main);
+    // the GIC and the timer follow from arch_interrupt_init and
+    // arch_timer_init once the kernel's interrupt layer exists
     aarch64_cache_init();
     aarch64_neon_init();
     aarch64_sve_init();
@@ -15,6 +15,7 @@
 
 #include "../hal/common/types.h"
 #include "config.h"
+#include <orion/fdt.h>
 
 // ============================================================================
 // ARCHITECTURE CONSTANTS
@@ -228,9 +229,12 @@ bool aarch64_has_feature(uint64_t feature);
 void aarch64_print_cpu_info(void);
 
 // MMU management
+int aarch64_mmu_setup(const fdt_region_t *ram, int count);
 void aarch64_mmu_init(void);
+bool aarch64_mmu_enabled(void);
 int aarch64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags);
 int aarch64_mmu_unmap_page(uint64_t va);
+uint64_t aarch64_mmu_translate(uint64_t va);
 void aarch64_mmu_invalidate_tlb(void);
 
 // Interrupt management
@@ -238,9 +242,23 @@ void aarch64_interrupts_init(void);
 void aarch64_interrupt_enable(uint32_t irq);
 void aarch64_interrupt_disable(uint32_t irq);
 void aarch64_interrupt_set_handler(uint32_t irq, void (*handler)(void));
+int aarch64_gicv3_init(const fdt_t *fdt);
+
+// PSCI and CPU bring-up
+int aarch64_psci_init(const fdt_t *fdt);
+int aarch64_cpus_init(const fdt_t *fdt);
+uint32_t aarch64_cpu_count(void);
+uint64_t aarch64_cpu_mpidr(uint32_t cpu);
+bool aarch64_cpu_online(uint32_t cpu);
+int aarch64_psci_cpu_on(uint32_t cpu);
+void aarch64_secondary_main(uint64_t cpu);
+void aarch64_psci_system_off(void);
+void aarch64_psci_system_reset(void);
 
 // Timer management
 void aarch64_timer_init(void);
+void aarch64_timer_cpu_init(void);
+uint64_t aarch64_timer_get_frequency(void);
 uint64_t aarch64_timer_read_ns(void);
 int aarch64_timer_set_oneshot(uint64_t deadline_ns);
 
@@ -274,6 +292,7 @@ uint32_t aarch64_numa_get_node_count(void);
 uint32_t aarch64_numa_get_current_node(void);
 
 // Main initialization
+void aarch64_boot_main(uint64_t dtb);
 void aarch64_arch_init(void);
 
 // Exception handlers (C functions called from assembly)
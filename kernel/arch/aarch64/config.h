@@ -216,6 +216,8 @@
 #define AARCH64_MAX_NUMA_NODES 8
 #define AARCH64_MAX_CLUSTERS 4
 #define AARCH64_MAX_CORES_PER_CLUSTER 8
+#define AARCH64_MAX_CPUS 8 // CPUs brought up through PSCI
+#define AARCH64_IPI_SGI 0  // SGI carrying the kernel's IPI messages
 
 // ============================================================================
 // CACHE CONFIGURATION
@@ -253,6 +255,8 @@
 // Generic timer configuration
 #define AARCH64_TIMER_FREQ_DEFAULT 24000000ULL // 24 MHz default
 #define AARCH64_TIMER_CNTFRQ_EL0 true
+#define AARCH64_TIMER_TICK_HZ 100
+#define AARCH64_TIMER_PPI_NS_PHYS 30 // EL1 physical timer, when the device tree has no timer node
 
 // ============================================================================
 // POWER MANAGEMENT
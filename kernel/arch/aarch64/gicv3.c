@@ -0,0 +1,430 @@
+/*
+ * Orion Operating System - aarch64 GICv3 Interrupt Controller
+ *
+ * GICv3 driver registered with the orion-irq layer. The distributor
+ * routes SPIs by affinity, each CPU has a redistributor for its SGIs and
+ * PPIs, and the CPU interface is reached through the ICC system
+ * registers. All interrupts are non-secure group 1 with a single
+ * priority-drop-and-deactivate EOI. Base addresses come from the
+ * "arm,gic-v3" device tree node, falling back to QEMU virt's layout.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include <orion/virtio_mmio.h>
+#include "arch.h"
+
+// ========================================
+// REGISTERS
+// ========================================
+
+#define GICD_CTLR 0x0000
+#define GICD_TYPER 0x0004
+#define GICD_IGROUPR 0x0080
+#define GICD_ISENABLER 0x0100
+#define GICD_ICENABLER 0x0180
+#define GICD_ICPENDR 0x0280
+#define GICD_IPRIORITYR 0x0400
+#define GICD_ICFGR 0x0C00
+#define GICD_IROUTER 0x6000
+
+#define GICD_CTLR_RWP (1U << 31)
+#define GICD_CTLR_ARE_NS (1U << 4)
+#define GICD_CTLR_ENABLE_G1A (1U << 1)
+#define GICD_CTLR_ENABLE_G1 (1U << 0)
+
+// Redistributor: RD_base frame then SGI_base frame
+#define GICR_CTLR 0x0000
+#define GICR_TYPER 0x0008
+#define GICR_WAKER 0x0014
+#define GICR_SGI_BASE 0x10000
+#define GICR_FRAME_STRIDE 0x20000
+#define GICR_VLPI_STRIDE 0x40000
+
+#define GICR_TYPER_VLPIS (1ULL << 1)
+#define GICR_TYPER_LAST (1ULL << 4)
+#define GICR_WAKER_PROCESSOR_SLEEP (1U << 1)
+#define GICR_WAKER_CHILDREN_ASLEEP (1U << 2)
+
+#define GICR_IGROUPR0 0x0080
+#define GICR_ISENABLER0 0x0100
+#define GICR_ICENABLER0 0x0180
+#define GICR_IPRIORITYR0 0x0400
+#define GICR_ICFGR0 0x0C00
+
+// ICC system registers by encoding, so that no GIC-aware assembler is needed
+#define ICC_PMR_EL1 "S3_0_C4_C6_0"
+#define ICC_IAR1_EL1 "S3_0_C12_C12_0"
+#define ICC_EOIR1_EL1 "S3_0_C12_C12_1"
+#define ICC_BPR1_EL1 "S3_0_C12_C12_3"
+#define ICC_CTLR_EL1 "S3_0_C12_C12_4"
+#define ICC_SRE_EL1 "S3_0_C12_C12_5"
+#define ICC_IGRPEN1_EL1 "S3_0_C12_C12_7"
+#define ICC_SGI1R_EL1 "S3_0_C12_C11_5"
+
+#define GIC_SPURIOUS_FIRST 1020
+#define GIC_SPI_BASE 32
+#define GIC_PPI_BASE 16
+#define GIC_WAIT_LOOPS 1000000
+
+// QEMU virt defaults
+#define GICV3_VIRT_DIST_BASE 0x08000000ULL
+#define GICV3_VIRT_REDIST_BASE 0x080A0000ULL
+#define GICV3_VIRT_REDIST_SIZE 0x00F60000ULL
+
+static uint8_t *g_gicd = NULL;
+static uint8_t *g_gicr_region = NULL;
+static uint64_t g_gicr_region_size = 0;
+static uint8_t *g_gicr[AARCH64_MAX_CPUS];
+static uint32_t g_nr_irqs = 0;
+
+static inline uint32_t mmio_read32(uint8_t *base, uint32_t reg)
+{
+    return *(volatile uint32_t *)(base + reg);
+}
+
+static inline void mmio_write32(uint8_t *base, uint32_t reg, uint32_t value)
+{
+    *(volatile uint32_t *)(base + reg) = value;
+}
+
+static inline uint64_t mmio_read64(uint8_t *base, uint32_t reg)
+{
+    return *(volatile uint64_t *)(base + reg);
+}
+
+static inline void mmio_write64(uint8_t *base, uint32_t reg, uint64_t value)
+{
+    *(volatile uint64_t *)(base + reg) = value;
+}
+
+static void gicd_wait_rwp(void)
+{
+    for (int i = 0; i < GIC_WAIT_LOOPS; i++)
+    {
+        if (!(mmio_read32(g_gicd, GICD_CTLR) & GICD_CTLR_RWP))
+        {
+            return;
+        }
+    }
+    kerror("GICv3: distributor register write timed out");
+}
+
+// MPIDR affinity in the Aff3.Aff2.Aff1.Aff0 layout of GICD_IROUTER and
+// GICR_TYPER
+static uint64_t gic_affinity(uint64_t mpidr)
+{
+    return (mpidr & 0xFFFFFFULL) | ((mpidr >> 32) & 0xFF) << 32;
+}
+
+static uint8_t *gic_current_sgi_base(void)
+{
+    uint8_t *gicr = g_gicr[arch_get_current_cpu() % AARCH64_MAX_CPUS];
+    return gicr ? gicr + GICR_SGI_BASE : NULL;
+}
+
+// ========================================
+// IRQ CHIP OPERATIONS
+// ========================================
+
+static void gicv3_enable(uint32_t irq)
+{
+    if (irq < GIC_SPI_BASE)
+    {
+        uint8_t *sgi = gic_current_sgi_base();
+        if (sgi)
+        {
+            mmio_write32(sgi, GICR_ISENABLER0, 1U << irq);
+        }
+        return;
+    }
+    mmio_write32(g_gicd, GICD_ISENABLER + (irq / 32) * 4, 1U << (irq % 32));
+}
+
+static void gicv3_disable(uint32_t irq)
+{
+    if (irq < GIC_SPI_BASE)
+    {
+        uint8_t *sgi = gic_current_sgi_base();
+        if (sgi)
+        {
+            mmio_write32(sgi, GICR_ICENABLER0, 1U << irq);
+        }
+        return;
+    }
+    mmio_write32(g_gicd, GICD_ICENABLER + (irq / 32) * 4, 1U << (irq % 32));
+    gicd_wait_rwp();
+}
+
+static uint32_t gicv3_ack(void)
+{
+    uint64_t iar;
+    __asm__ volatile("mrs %0, " ICC_IAR1_EL1 : "=r"(iar));
+    __asm__ volatile("dsb sy" ::: "memory");
+    uint32_t irq = (uint32_t)(iar & 0xFFFFFF);
+    return irq >= GIC_SPURIOUS_FIRST && irq < 1024 ? IRQ_NONE : irq;
+}
+
+static void gicv3_eoi(uint32_t irq)
+{
+    __asm__ volatile("msr " ICC_EOIR1_EL1 ", %0" ::"r"((uint64_t)irq));
+    __asm__ volatile("isb" ::: "memory");
+}
+
+static int gicv3_set_type(uint32_t irq, uint32_t type)
+{
+    if (irq < GIC_PPI_BASE)
+    {
+        return -OR_EINVAL; // SGIs are always edge-triggered
+    }
+    uint8_t *base = irq < GIC_SPI_BASE ? gic_current_sgi_base() : g_gicd;
+    uint32_t reg = (irq < GIC_SPI_BASE ? GICR_ICFGR0 : GICD_ICFGR) + (irq / 16) * 4;
+    if (!base)
+    {
+        return -OR_ENODEV;
+    }
+    uint32_t shift = (irq % 16) * 2 + 1;
+    uint32_t value = mmio_read32(base, reg) & ~(1U << shift);
+    if (type == IRQ_TYPE_EDGE_RISING)
+    {
+        value |= 1U << shift;
+    }
+    mmio_write32(base, reg, value);
+    return OR_OK;
+}
+
+static int gicv3_set_priority(uint32_t irq, uint8_t priority)
+{
+    uint8_t *base = irq < GIC_SPI_BASE ? gic_current_sgi_base() : g_gicd;
+    if (!base)
+    {
+        return -OR_ENODEV;
+    }
+    uint32_t reg = (irq < GIC_SPI_BASE ? GICR_IPRIORITYR0 : GICD_IPRIORITYR) + irq;
+    *(volatile uint8_t *)(base + reg) = priority;
+    return OR_OK;
+}
+
+static int gicv3_set_affinity(uint32_t irq, uint32_t cpu)
+{
+    if (irq < GIC_SPI_BASE)
+    {
+        return -OR_EINVAL; // Banked per CPU
+    }
+    if (cpu >= aarch64_cpu_count())
+    {
+        return -OR_EINVAL;
+    }
+    mmio_write64(g_gicd, GICD_IROUTER + irq * 8, gic_affinity(aarch64_cpu_mpidr(cpu)));
+    return OR_OK;
+}
+
+static int gicv3_send_ipi(uint32_t irq, uint32_t cpu)
+{
+    if (irq >= GIC_PPI_BASE || cpu >= aarch64_cpu_count())
+    {
+        return -OR_EINVAL;
+    }
+    uint64_t mpidr = aarch64_cpu_mpidr(cpu);
+    uint64_t aff0 = mpidr & 0xFF;
+    uint64_t sgi = ((uint64_t)irq << 24) | (1ULL << (aff0 % 16)) | ((aff0 / 16) << 44) |
+                   (((mpidr >> 8) & 0xFF) << 16) | (((mpidr >> 16) & 0xFF) << 32) | (((mpidr >> 32) & 0xFF) << 48);
+    __asm__ volatile("dsb ishst" ::: "memory");
+    __asm__ volatile("msr " ICC_SGI1R_EL1 ", %0" ::"r"(sgi));
+    __asm__ volatile("isb" ::: "memory");
+    return OR_OK;
+}
+
+// Find the redistributor of the running CPU, wake it and set up its SGIs
+// and PPIs, then enable the CPU interface
+static int gicv3_cpu_init(uint32_t cpu)
+{
+    if (cpu >= AARCH64_MAX_CPUS)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint64_t mpidr;
+    __asm__ volatile("mrs %0, MPIDR_EL1" : "=r"(mpidr));
+    uint64_t affinity = gic_affinity(mpidr);
+
+    uint8_t *gicr = NULL;
+    for (uint64_t offset = 0; offset < g_gicr_region_size;)
+    {
+        uint8_t *frame = g_gicr_region + offset;
+        uint64_t typer = mmio_read64(frame, GICR_TYPER);
+        if ((typer >> 32) == affinity)
+        {
+            gicr = frame;
+            break;
+        }
+        if (typer & GICR_TYPER_LAST)
+        {
+            break;
+        }
+        offset += (typer & GICR_TYPER_VLPIS) ? GICR_VLPI_STRIDE : GICR_FRAME_STRIDE;
+    }
+    if (!gicr)
+    {
+        kerror("GICv3: no redistributor for CPU %u (MPIDR 0x%llx)", cpu, (unsigned long long)mpidr);
+        return -OR_ENODEV;
+    }
+    g_gicr[cpu] = gicr;
+
+    uint32_t waker = mmio_read32(gicr, GICR_WAKER) & ~GICR_WAKER_PROCESSOR_SLEEP;
+    mmio_write32(gicr, GICR_WAKER, waker);
+    for (int i = 0; i < GIC_WAIT_LOOPS && (mmio_read32(gicr, GICR_WAKER) & GICR_WAKER_CHILDREN_ASLEEP); i++)
+    {
+        arch_pause();
+    }
+
+    uint8_t *sgi = gicr + GICR_SGI_BASE;
+    mmio_write32(sgi, GICR_IGROUPR0, 0xFFFFFFFF);
+    mmio_write32(sgi, GICR_ICENABLER0, 0xFFFF0000); // PPIs off until requested
+    mmio_write32(sgi, GICR_ISENABLER0, 0x0000FFFF); // SGIs always on
+    for (uint32_t irq = 0; irq < GIC_SPI_BASE; irq += 4)
+    {
+        mmio_write32(sgi, GICR_IPRIORITYR0 + irq, 0x01010101U * IRQ_PRIORITY_DEFAULT);
+    }
+
+    uint64_t sre;
+    __asm__ volatile("mrs %0, " ICC_SRE_EL1 : "=r"(sre));
+    __asm__ volatile("msr " ICC_SRE_EL1 ", %0\n"
+                     "isb" ::"r"(sre | 1));
+    __asm__ volatile("msr " ICC_PMR_EL1 ", %0" ::"r"(0xFFULL));
+    __asm__ volatile("msr " ICC_BPR1_EL1 ", %0" ::"r"(0ULL));
+    __asm__ volatile("msr " ICC_CTLR_EL1 ", %0" ::"r"(0ULL));
+    __asm__ volatile("msr " ICC_IGRPEN1_EL1 ", %0\n"
+                     "isb" ::"r"(1ULL));
+    return OR_OK;
+}
+
+// Device tree specifiers are <type number flags>: type 0 for SPIs, 1 for
+// PPIs; flags 1 for rising edge, 4 for high level
+static int gicv3_xlate(const uint32_t *cells, uint32_t count, uint32_t *irq, uint32_t *type)
+{
+    if (count < 3)
+    {
+        return -OR_EINVAL;
+    }
+    switch (cells[0])
+    {
+    case 0:
+        *irq = GIC_SPI_BASE + cells[1];
+        break;
+    case 1:
+        *irq = GIC_PPI_BASE + cells[1];
+        break;
+    default:
+        return -OR_ENOTSUP;
+    }
+    *type = (cells[2] & 0x3) ? IRQ_TYPE_EDGE_RISING : IRQ_TYPE_LEVEL_HIGH;
+    return *irq < g_nr_irqs ? OR_OK : -OR_EINVAL;
+}
+
+static irq_chip_t g_gicv3_chip = {
+    .name = "gicv3",
+    .enable = gicv3_enable,
+    .disable = gicv3_disable,
+    .ack = gicv3_ack,
+    .eoi = gicv3_eoi,
+    .set_type = gicv3_set_type,
+    .set_priority = gicv3_set_priority,
+    .set_affinity = gicv3_set_affinity,
+    .send_ipi = gicv3_send_ipi,
+    .cpu_init = gicv3_cpu_init,
+    .xlate = gicv3_xlate,
+};
+
+// ========================================
+// INITIALIZATION
+// ========================================
+
+int aarch64_gicv3_init(const fdt_t *fdt)
+{
+    uint64_t dist = GICV3_VIRT_DIST_BASE;
+    uint64_t redist = GICV3_VIRT_REDIST_BASE;
+    uint64_t redist_size = GICV3_VIRT_REDIST_SIZE;
+    int node = fdt ? fdt_node_offset_by_compatible(fdt, -1, "arm,gic-v3") : -1;
+    if (node >= 0)
+    {
+        fdt_read_reg(fdt, node, 0, &dist, NULL);
+        fdt_read_reg(fdt, node, 1, &redist, &redist_size);
+    }
+    else
+    {
+        kwarn("GICv3: no arm,gic-v3 node, assuming the QEMU virt layout");
+    }
+
+    g_gicd = (uint8_t *)arch_ioremap(dist, 0x10000);
+    g_gicr_region = (uint8_t *)arch_ioremap(redist, redist_size);
+    g_gicr_region_size = redist_size;
+    if (!g_gicd || !g_gicr_region)
+    {
+        return -OR_ENOMEM;
+    }
+
+    uint32_t typer = mmio_read32(g_gicd, GICD_TYPER);
+    g_nr_irqs = ((typer & 0x1F) + 1) * 32;
+    if (g_nr_irqs > GIC_SPURIOUS_FIRST)
+    {
+        g_nr_irqs = GIC_SPURIOUS_FIRST;
+    }
+    g_gicv3_chip.nr_irqs = g_nr_irqs;
+
+    // Distributor off while the SPIs are configured
+    mmio_write32(g_gicd, GICD_CTLR, 0);
+    gicd_wait_rwp();
+
+    uint64_t mpidr;
+    __asm__ volatile("mrs %0, MPIDR_EL1" : "=r"(mpidr));
+    for (uint32_t irq = GIC_SPI_BASE; irq < g_nr_irqs; irq += 32)
+    {
+        mmio_write32(g_gicd, GICD_IGROUPR + (irq / 32) * 4, 0xFFFFFFFF);
+        mmio_write32(g_gicd, GICD_ICENABLER + (irq / 32) * 4, 0xFFFFFFFF);
+        mmio_write32(g_gicd, GICD_ICPENDR + (irq / 32) * 4, 0xFFFFFFFF);
+    }
+    for (uint32_t irq = GIC_SPI_BASE; irq < g_nr_irqs; irq += 4)
+    {
+        mmio_write32(g_gicd, GICD_IPRIORITYR + irq, 0x01010101U * IRQ_PRIORITY_DEFAULT);
+    }
+    for (uint32_t irq = GIC_SPI_BASE; irq < g_nr_irqs; irq += 16)
+    {
+        mmio_write32(g_gicd, GICD_ICFGR + (irq / 16) * 4, 0); // Level-triggered
+    }
+    for (uint32_t irq = GIC_SPI_BASE; irq < g_nr_irqs; irq++)
+    {
+        mmio_write64(g_gicd, GICD_IROUTER + irq * 8, gic_affinity(mpidr));
+    }
+    gicd_wait_rwp();
+
+    mmio_write32(g_gicd, GICD_CTLR, GICD_CTLR_ARE_NS | GICD_CTLR_ENABLE_G1A | GICD_CTLR_ENABLE_G1);
+    gicd_wait_rwp();
+
+    int result = gicv3_cpu_init(0);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+    result = irq_register_chip(&g_gicv3_chip);
+    if (result == OR_OK)
+    {
+        kinfo("GICv3: distributor 0x%llx, redistributors 0x%llx, %u lines", (unsigned long long)dist,
+              (unsigned long long)redist, g_nr_irqs);
+    }
+    return result;
+}
+
+// IRQ vector entry, called from arch_asm.S with the registers saved
+void aarch64_irq_exception_handler(void)
+{
+    irq_handle();
+}
@@ -0,0 +1,439 @@
+/*
+ * Orion Operating System - aarch64 MMU
+ *
+ * Stage 1 translation for EL1 with the 4 KiB granule and 48-bit virtual
+ * addresses: four levels of 512-entry tables behind TTBR0_EL1, TTBR1
+ * walks disabled. The kernel runs identity-mapped: RAM reported by the
+ * device tree is mapped as normal write-back memory with 2 MiB blocks and
+ * the device window below it (the first GiB on QEMU virt) as
+ * Device-nGnRE. Blocks are split into pages when a page inside them is
+ * remapped or unmapped.
+ *
+ * Table pages come from a static pool until the physical allocator is up.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/fdt.h>
+#include <orion/smp.h>
+#include "arch.h"
+
+// ========================================
+// DESCRIPTORS
+// ========================================
+
+#define PTE_VALID (1ULL << 0)
+#define PTE_TABLE (1ULL << 1) // Table at levels 0-2, page at level 3
+#define PTE_BLOCK 0ULL
+#define PTE_ATTR_INDEX(i) ((uint64_t)(i) << 2)
+#define PTE_AP_USER (1ULL << 6)
+#define PTE_AP_RO (1ULL << 7)
+#define PTE_SH_INNER (3ULL << 8)
+#define PTE_AF (1ULL << 10)
+#define PTE_NG (1ULL << 11)
+#define PTE_PXN (1ULL << 53)
+#define PTE_UXN (1ULL << 54)
+#define PTE_ADDR_MASK 0x0000FFFFFFFFF000ULL
+#define PTE_ATTR_MASK (~PTE_ADDR_MASK & ~(PTE_VALID | PTE_TABLE))
+
+#define PT_ENTRIES 512
+#define PT_LEVELS 4
+
+// MAIR_EL1 slots
+#define MAIR_IDX_NORMAL 0
+#define MAIR_IDX_DEVICE 1
+#define MAIR_IDX_NORMAL_NC 2
+#define MAIR_VALUE ((0xFFULL << (8 * MAIR_IDX_NORMAL)) | (0x04ULL << (8 * MAIR_IDX_DEVICE)) | \
+                    (0x44ULL << (8 * MAIR_IDX_NORMAL_NC)))
+
+// TCR_EL1: 48-bit TTBR0 space, write-back inner shareable walks, 4 KiB
+// granule, TTBR1 walks disabled
+#define TCR_T0SZ(bits) ((uint64_t)(64 - (bits)) << 0)
+#define TCR_IRGN0_WBWA (1ULL << 8)
+#define TCR_ORGN0_WBWA (1ULL << 10)
+#define TCR_SH0_INNER (3ULL << 12)
+#define TCR_TG0_4K (0ULL << 14)
+#define TCR_EPD1 (1ULL << 23)
+#define TCR_IPS_SHIFT 32
+
+#define SCTLR_M (1ULL << 0)
+#define SCTLR_C (1ULL << 2)
+#define SCTLR_I (1ULL << 12)
+
+#define MMU_VA_BITS 48
+#define MMU_BOOT_POOL_PAGES 64
+
+// Physical window holding the platform devices on QEMU virt (GIC, UART,
+// RTC, virtio-mmio, PCIe ECAM)
+#define MMU_DEVICE_WINDOW_BASE 0x00000000ULL
+#define MMU_DEVICE_WINDOW_SIZE 0x40000000ULL
+
+static uint64_t g_boot_pool[MMU_BOOT_POOL_PAGES][PT_ENTRIES] __attribute__((aligned(AARCH64_PAGE_SIZE)));
+static uint32_t g_boot_pool_used = 0;
+static uint64_t *g_root_table = NULL;
+static spinlock_t g_mmu_lock = SPINLOCK_INIT;
+static bool g_mmu_enabled = false;
+
+static uint64_t *mmu_alloc_table(void)
+{
+    uint64_t *table = NULL;
+    if (g_boot_pool_used < MMU_BOOT_POOL_PAGES)
+    {
+        table = g_boot_pool[g_boot_pool_used++];
+    }
+    else
+    {
+        // Identity-mapped: the physical address is usable as is
+        table = (uint64_t *)(uintptr_t)pmm_alloc_page();
+    }
+    if (table)
+    {
+        memset(table, 0, AARCH64_PAGE_SIZE);
+    }
+    return table;
+}
+
+static inline uint32_t mmu_index(uint64_t va, int level)
+{
+    return (va >> (39 - 9 * level)) & (PT_ENTRIES - 1);
+}
+
+static inline uint64_t mmu_level_size(int level)
+{
+    return 1ULL << (39 - 9 * level);
+}
+
+static uint64_t mmu_attributes(uint64_t flags)
+{
+    uint64_t attr = PTE_AF;
+    if (flags & PAGE_FLAG_NO_CACHE)
+    {
+        attr |= PTE_ATTR_INDEX(MAIR_IDX_DEVICE) | PTE_PXN | PTE_UXN;
+    }
+    else
+    {
+        attr |= PTE_ATTR_INDEX(MAIR_IDX_NORMAL) | PTE_SH_INNER;
+    }
+    if (!(flags & PAGE_FLAG_WRITE))
+    {
+        attr |= PTE_AP_RO;
+    }
+    if (flags & PAGE_FLAG_USER)
+    {
+        // The kernel never executes user pages
+        attr |= PTE_AP_USER | PTE_NG | PTE_PXN;
+        if (!(flags & PAGE_FLAG_EXEC))
+        {
+            attr |= PTE_UXN;
+        }
+    }
+    else
+    {
+        attr |= PTE_UXN;
+        if (!(flags & PAGE_FLAG_EXEC))
+        {
+            attr |= PTE_PXN;
+        }
+    }
+    return attr;
+}
+
+static inline void mmu_flush_page(uint64_t va)
+{
+    __asm__ volatile("dsb ishst\n"
+                     "tlbi vaae1is, %0\n"
+                     "dsb ish\n"
+                     "isb" ::"r"(va >> 12)
+                     : "memory");
+}
+
+// Replace the block at `entry` (level 1 or 2) by a table of blocks or pages
+// with the same attributes
+static int mmu_split_block(uint64_t *entry, int level)
+{
+    uint64_t *table = mmu_alloc_table();
+    if (!table)
+    {
+        return -OR_ENOMEM;
+    }
+    uint64_t base = *entry & PTE_ADDR_MASK;
+    uint64_t attr = *entry & PTE_ATTR_MASK;
+    uint64_t step = mmu_level_size(level + 1);
+    uint64_t type = (level + 1 == PT_LEVELS - 1) ? PTE_TABLE : PTE_BLOCK;
+    for (uint32_t i = 0; i < PT_ENTRIES; i++)
+    {
+        table[i] = (base + i * step) | attr | type | PTE_VALID;
+    }
+    __asm__ volatile("dsb ishst" ::: "memory");
+    *entry = (uint64_t)(uintptr_t)table | PTE_TABLE | PTE_VALID;
+    return OR_OK;
+}
+
+// Entry describing `va` at `level`, creating or splitting the tables
+// above it when `create` is set
+static uint64_t *mmu_walk(uint64_t va, int level, bool create)
+{
+    uint64_t *table = g_root_table;
+    for (int current = 0; current < level; current++)
+    {
+        uint64_t *entry = &table[mmu_index(va, current)];
+        if (!(*entry & PTE_VALID))
+        {
+            if (!create)
+            {
+                return NULL;
+            }
+            uint64_t *next = mmu_alloc_table();
+            if (!next)
+            {
+                return NULL;
+            }
+            *entry = (uint64_t)(uintptr_t)next | PTE_TABLE | PTE_VALID;
+        }
+        else if (!(*entry & PTE_TABLE))
+        {
+            if (!create || mmu_split_block(entry, current) != OR_OK)
+            {
+                return NULL;
+            }
+        }
+        table = (uint64_t *)(uintptr_t)(*entry & PTE_ADDR_MASK);
+    }
+    return &table[mmu_index(va, level)];
+}
+
+// Map [pa, pa + size) at va with the largest blocks alignment allows
+static int mmu_map_range(uint64_t va, uint64_t pa, uint64_t size, uint64_t flags)
+{
+    uint64_t attr = mmu_attributes(flags);
+    uint64_t end = va + size;
+    while (va < end)
+    {
+        int level = PT_LEVELS - 1;
+        for (int candidate = 1; candidate < PT_LEVELS - 1; candidate++)
+        {
+            uint64_t block = mmu_level_size(candidate);
+            if (((va | pa) & (block - 1)) == 0 && end - va >= block)
+            {
+                level = candidate;
+                break;
+            }
+        }
+        uint64_t *entry = mmu_walk(va, level, true);
+        if (!entry)
+        {
+            return -OR_ENOMEM;
+        }
+        *entry = pa | attr | (level == PT_LEVELS - 1 ? PTE_TABLE : PTE_BLOCK) | PTE_VALID;
+        va += mmu_level_size(level);
+        pa += mmu_level_size(level);
+    }
+    return OR_OK;
+}
+
+// ========================================
+// SETUP
+// ========================================
+
+// Program the translation registers and turn the MMU on. Used by the boot
+// CPU once the tables exist and by every secondary CPU
+void aarch64_mmu_init(void)
+{
+    if (!g_root_table)
+    {
+        return;
+    }
+
+    uint64_t mmfr0;
+    __asm__ volatile("mrs %0, ID_AA64MMFR0_EL1" : "=r"(mmfr0));
+    uint64_t tcr = TCR_T0SZ(MMU_VA_BITS) | TCR_IRGN0_WBWA | TCR_ORGN0_WBWA | TCR_SH0_INNER | TCR_TG0_4K |
+                   TCR_EPD1 | ((mmfr0 & 0x7) << TCR_IPS_SHIFT);
+
+    __asm__ volatile("msr MAIR_EL1, %0" ::"r"(MAIR_VALUE));
+    __asm__ volatile("msr TCR_EL1, %0" ::"r"(tcr));
+    __asm__ volatile("msr TTBR0_EL1, %0" ::"r"((uint64_t)(uintptr_t)g_root_table));
+    __asm__ volatile("dsb ish\n"
+                     "tlbi vmalle1\n"
+                     "dsb ish\n"
+                     "isb" ::
+                         : "memory");
+
+    uint64_t sctlr;
+    __asm__ volatile("mrs %0, SCTLR_EL1" : "=r"(sctlr));
+    sctlr |= SCTLR_M | SCTLR_C | SCTLR_I;
+    __asm__ volatile("msr SCTLR_EL1, %0\n"
+                     "isb" ::"r"(sctlr)
+                     : "memory");
+    g_mmu_enabled = true;
+}
+
+int aarch64_mmu_setup(const fdt_region_t *ram, int count)
+{
+    g_root_table = mmu_alloc_table();
+    if (!g_root_table)
+    {
+        return -OR_ENOMEM;
+    }
+
+    int result = mmu_map_range(MMU_DEVICE_WINDOW_BASE, MMU_DEVICE_WINDOW_BASE, MMU_DEVICE_WINDOW_SIZE,
+                               PAGE_FLAG_WRITE | PAGE_FLAG_NO_CACHE);
+    for (int i = 0; i < count && result == OR_OK; i++)
+    {
+        // The kernel image lives in RAM, which therefore stays executable
+        // until the loader maps sections individually
+        uint64_t base = ram[i].base & ~(uint64_t)AARCH64_PAGE_MASK;
+        uint64_t size = (ram[i].size + AARCH64_PAGE_MASK) & ~(uint64_t)AARCH64_PAGE_MASK;
+        result = mmu_map_range(base, base, size, PAGE_FLAG_WRITE | PAGE_FLAG_EXEC);
+    }
+    if (result != OR_OK)
+    {
+        kerror("aarch64: page tables exhausted while mapping RAM");
+        return result;
+    }
+
+    aarch64_mmu_init();
+    kinfo("aarch64: MMU on, %u table pages, %d RAM regions identity-mapped", g_boot_pool_used, count);
+    return OR_OK;
+}
+
+bool aarch64_mmu_enabled(void)
+{
+    return g_mmu_enabled;
+}
+
+// ========================================
+// PAGE MAPPINGS
+// ========================================
+
+int aarch64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags)
+{
+    if ((va | pa) & AARCH64_PAGE_MASK || !g_root_table)
+    {
+        return -OR_EINVAL;
+    }
+
+    spin_lock(&g_mmu_lock);
+    uint64_t *entry = mmu_walk(va, PT_LEVELS - 1, true);
+    if (!entry)
+    {
+        spin_unlock(&g_mmu_lock);
+        return -OR_ENOMEM;
+    }
+    bool remap = *entry & PTE_VALID;
+    *entry = pa | mmu_attributes(flags) | PTE_TABLE | PTE_VALID;
+    spin_unlock(&g_mmu_lock);
+
+    if (remap)
+    {
+        mmu_flush_page(va);
+    }
+    else
+    {
+        __asm__ volatile("dsb ishst\nisb" ::: "memory");
+    }
+    return OR_OK;
+}
+
+int aarch64_mmu_unmap_page(uint64_t va)
+{
+    if (va & AARCH64_PAGE_MASK || !g_root_table)
+    {
+        return -OR_EINVAL;
+    }
+    if (!aarch64_mmu_translate(va))
+    {
+        return -OR_ENOENT;
+    }
+
+    // Walking with `create` splits a block covering the page
+    spin_lock(&g_mmu_lock);
+    uint64_t *entry = mmu_walk(va, PT_LEVELS - 1, true);
+    if (!entry)
+    {
+        spin_unlock(&g_mmu_lock);
+        return -OR_ENOMEM;
+    }
+    *entry = 0;
+    spin_unlock(&g_mmu_lock);
+
+    mmu_flush_page(va);
+    return OR_OK;
+}
+
+// Invalidation on the running CPU only, for smp_tlb_shootdown. Mappings
+// changed here are already invalidated in every CPU by the inner
+// shareable TLBI of mmu_flush_page, so this only matters to callers that
+// edit tables themselves
+void arch_tlb_flush_local(uint64_t vaddr, uint64_t pages)
+{
+    if (pages == SMP_TLB_FLUSH_ALL)
+    {
+        __asm__ volatile("dsb nshst\n"
+                         "tlbi vmalle1\n"
+                         "dsb nsh\n"
+                         "isb" ::
+                             : "memory");
+        return;
+    }
+    __asm__ volatile("dsb nshst" ::: "memory");
+    for (uint64_t page = 0; page < pages; page++)
+    {
+        __asm__ volatile("tlbi vaae1, %0" ::"r"((vaddr + page * AARCH64_PAGE_SIZE) >> 12) : "memory");
+    }
+    __asm__ volatile("dsb nsh\n"
+                     "isb" ::
+                         : "memory");
+}
+
+// Physical address mapped at `va`, 0 when unmapped
+uint64_t aarch64_mmu_translate(uint64_t va)
+{
+    if (!g_root_table)
+    {
+        return 0;
+    }
+    uint64_t *table = g_root_table;
+    for (int level = 0; level < PT_LEVELS; level++)
+    {
+        uint64_t entry = table[mmu_index(va, level)];
+        if (!(entry & PTE_VALID))
+        {
+            return 0;
+        }
+        bool leaf = level == PT_LEVELS - 1 || !(entry & PTE_TABLE);
+        if (leaf)
+        {
+            uint64_t size = mmu_level_size(level);
+            return (entry & PTE_ADDR_MASK & ~(size - 1)) | (va & (size - 1));
+        }
+        table = (uint64_t *)(uintptr_t)(entry & PTE_ADDR_MASK);
+    }
+    return 0;
+}
+
+void *arch_ioremap(uint64_t phys, size_t size)
+{
+    uint64_t start = phys & ~(uint64_t)AARCH64_PAGE_MASK;
+    uint64_t end = (phys + size + AARCH64_PAGE_MASK) & ~(uint64_t)AARCH64_PAGE_MASK;
+    for (uint64_t page = start; page < end; page += AARCH64_PAGE_SIZE)
+    {
+        // The device window is mapped at boot; only devices outside it
+        // need pages of their own
+        if (aarch64_mmu_translate(page) == page)
+        {
+            continue;
+        }
+        if (aarch64_mmu_map_page(page, page, PAGE_FLAG_WRITE | PAGE_FLAG_NO_CACHE) != OR_OK)
+        {
+            return NULL;
+        }
+    }
+    return (void *)(uintptr_t)phys;
+}
@@ -0,0 +1,179 @@
+/*
+ * Orion Operating System - aarch64 Platform Bring-up
+ *
+ * Glue between the boot code and the portable kernel on device tree
+ * machines such as QEMU's virt board. aarch64_boot_main runs at EL1 with
+ * the MMU off, the device tree blob address handed over by firmware in
+ * x0: it brings up the PL011 console, the identity mapped page tables and
+ * the boot information the kernel expects from the UEFI loader, then
+ * enters kernel_main. The interrupt controller, CPUs, virtio-mmio devices
+ * and timer are started later from the hooks the kernel calls during its
+ * early initialization.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include <orion/virtio_mmio.h>
+#include <orion/smp.h>
+#include <orion/earlycon.h>
+#include "orion-boot-protocol.h"
+#include "arch.h"
+
+#define PL011_DEFAULT_BASE 0x09000000ULL // QEMU virt UART0
+#define PL011_DR 0x00
+#define PL011_FR 0x18
+#define PL011_FR_TXFF (1U << 5)
+
+#define AARCH64_FALLBACK_RAM_BASE 0x40000000ULL
+#define AARCH64_FALLBACK_RAM_SIZE (128ULL * 1024 * 1024)
+
+
+extern void kernel_main(struct orion_boot_info *boot_info);
+
+static volatile uint32_t *g_uart = (volatile uint32_t *)PL011_DEFAULT_BASE;
+
+// ========================================
+// CONSOLE
+// ========================================
+
+// The UART is left configured by firmware (QEMU needs no setup at all);
+// only its address is taken from the device tree
+void console_init(void)
+{
+    const fdt_t *fdt = fdt_boot();
+    int node = fdt ? fdt_node_offset_by_compatible(fdt, -1, "arm,pl011") : -1;
+    uint64_t base;
+    if (node >= 0 && fdt_read_reg(fdt, node, 0, &base, NULL) == OR_OK)
+    {
+        g_uart = (volatile uint32_t *)(uintptr_t)base;
+    }
+}
+
+void console_putchar(char c)
+{
+    earlycon_putchar(c);
+    if (c == '\n')
+    {
+        console_putchar('\r');
+    }
+    while (g_uart[PL011_FR / 4] & PL011_FR_TXFF)
+    {
+    }
+    g_uart[PL011_DR / 4] = (uint32_t)(uint8_t)c;
+}
+
+void console_puts(const char *str)
+{
+    while (*str)
+    {
+        console_putchar(*str++);
+    }
+}
+
+// ========================================
+// BOOT
+// ========================================
+
+void aarch64_boot_main(uint64_t dtb)
+{
+    // CPU numbers live in TPIDR_EL1, the boot CPU being 0
+    __asm__ volatile("msr TPIDR_EL1, xzr");
+
+    fdt_set_boot((const void *)(uintptr_t)dtb);
+    console_init();
+    kinfo("aarch64: booting, device tree at 0x%llx", (unsigned long long)dtb);
+    if (!fdt_boot())
+    {
+        kwarn("aarch64: no valid device tree, using QEMU virt defaults");
+    }
+
+    fdt_region_t ram[FDT_MAX_MEMORY_REGIONS];
+    int count = fdt_boot() ? fdt_memory_regions(fdt_boot(), ram, FDT_MAX_MEMORY_REGIONS) : 0;
+    if (count <= 0)
+    {
+        ram[0].base = AARCH64_FALLBACK_RAM_BASE;
+        ram[0].size = AARCH64_FALLBACK_RAM_SIZE;
+        count = 1;
+    }
+
+    if (aarch64_mmu_setup(ram, count) != OR_OK)
+    {
+        kerror("aarch64: cannot build the kernel page tables");
+        arch_halt();
+    }
+    aarch64_arch_init();
+
+    kernel_main(fdt_build_boot_info(ram, count));
+    arch_halt();
+}
+
+// ========================================
+// KERNEL HOOKS
+// ========================================
+
+static void aarch64_ipi_handler(uint32_t irq, void *data)
+{
+    (void)irq;
+    (void)data;
+    smp_handle_ipi();
+}
+
+void arch_interrupt_init(void)
+{
+    const fdt_t *fdt = fdt_boot();
+    if (aarch64_gicv3_init(fdt) != OR_OK)
+    {
+        kerror("aarch64: no usable GICv3, interrupts stay masked");
+        return;
+    }
+    aarch64_cpus_init(fdt);
+    aarch64_psci_init(fdt);
+    irq_request(AARCH64_IPI_SGI, aarch64_ipi_handler, NULL, "ipi");
+    if (fdt)
+    {
+        virtio_mmio_discover(fdt);
+    }
+
+    __asm__ volatile("msr daifclr, #0x2" ::: "memory");
+}
+
+void arch_timer_init(void)
+{
+    aarch64_timer_init();
+}
+
+int arch_smp_send_ipi(uint32_t cpu)
+{
+    return irq_send_ipi(AARCH64_IPI_SGI, cpu);
+}
+
+void arch_enable_interrupts(void)
+{
+    __asm__ volatile("msr daifclr, #0x2" ::: "memory");
+}
+
+void arch_cpu_idle(void)
+{
+    __asm__ volatile("wfi");
+}
+
+void arch_pause(void)
+{
+    __asm__ volatile("yield");
+}
+
+void arch_halt(void)
+{
+    for (;;)
+    {
+        __asm__ volatile("wfi");
+    }
+}
@@ -0,0 +1,295 @@
+/*
+ * Orion Operating System - aarch64 PSCI and CPU Bring-up
+ *
+ * Power State Coordination Interface client and secondary CPU start. The
+ * CPUs are listed by the /cpus node of the device tree, their "reg" being
+ * the MPIDR affinity; CPU numbers used by the kernel are their position
+ * in that list, the boot CPU being renumbered 0. Secondary CPUs are
+ * started with PSCI CPU_ON at aarch64_secondary_entry (boot.S), given a
+ * stack of their own, and join the scheduler through
+ * aarch64_secondary_main when the kernel calls arch_smp_boot. The
+ * conduit (HVC under a hypervisor such as QEMU with KVM or the default
+ * virt firmware, SMC with TF-A) comes from the /psci node.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include <orion/smp.h>
+#include "arch.h"
+
+#define PSCI_0_2_FN_VERSION 0x84000000U
+#define PSCI_0_2_FN_CPU_OFF 0x84000002U
+#define PSCI_0_2_FN64_CPU_ON 0xC4000003U
+#define PSCI_0_2_FN64_AFFINITY_INFO 0xC4000004U
+#define PSCI_0_2_FN_SYSTEM_OFF 0x84000008U
+#define PSCI_0_2_FN_SYSTEM_RESET 0x84000009U
+
+#define PSCI_SUCCESS 0
+#define PSCI_NOT_SUPPORTED -1
+#define PSCI_INVALID_PARAMETERS -2
+#define PSCI_DENIED -3
+#define PSCI_ALREADY_ON -4
+#define PSCI_ON_PENDING -5
+
+#define AARCH64_SECONDARY_STACK_SIZE 16384
+#define AARCH64_CPU_ON_TIMEOUT_LOOPS 100000000ULL
+
+#define MPIDR_AFFINITY_MASK 0xFF00FFFFFFULL
+
+typedef enum psci_conduit
+{
+    PSCI_CONDUIT_NONE = 0,
+    PSCI_CONDUIT_HVC,
+    PSCI_CONDUIT_SMC,
+} psci_conduit_t;
+
+static psci_conduit_t g_psci_conduit = PSCI_CONDUIT_NONE;
+static uint32_t g_psci_version = 0;
+
+static uint64_t g_cpu_mpidr[AARCH64_MAX_CPUS];
+static uint32_t g_cpu_count = 1;
+static volatile bool g_cpu_online[AARCH64_MAX_CPUS];
+
+static uint8_t g_secondary_stacks[AARCH64_MAX_CPUS][AARCH64_SECONDARY_STACK_SIZE] __attribute__((aligned(16)));
+// Read by aarch64_secondary_entry before any C code runs on the new CPU
+uint64_t aarch64_cpu_stack_top[AARCH64_MAX_CPUS];
+
+extern void aarch64_secondary_entry(void);
+
+static int64_t psci_call(uint32_t function, uint64_t arg0, uint64_t arg1, uint64_t arg2)
+{
+    register uint64_t x0 __asm__("x0") = function;
+    register uint64_t x1 __asm__("x1") = arg0;
+    register uint64_t x2 __asm__("x2") = arg1;
+    register uint64_t x3 __asm__("x3") = arg2;
+
+    switch (g_psci_conduit)
+    {
+    case PSCI_CONDUIT_HVC:
+        __asm__ volatile("hvc #0" : "+r"(x0) : "r"(x1), "r"(x2), "r"(x3) : "memory");
+        break;
+    case PSCI_CONDUIT_SMC:
+        __asm__ volatile("smc #0" : "+r"(x0) : "r"(x1), "r"(x2), "r"(x3) : "memory");
+        break;
+    default:
+        return PSCI_NOT_SUPPORTED;
+    }
+    return (int64_t)x0;
+}
+
+static int psci_to_error(int64_t result)
+{
+    switch (result)
+    {
+    case PSCI_SUCCESS:
+        return OR_OK;
+    case PSCI_NOT_SUPPORTED:
+        return -OR_ENOTSUP;
+    case PSCI_INVALID_PARAMETERS:
+        return -OR_EINVAL;
+    case PSCI_DENIED:
+        return -OR_EPERM;
+    case PSCI_ALREADY_ON:
+    case PSCI_ON_PENDING:
+        return -OR_EBUSY;
+    default:
+        return -OR_EIO;
+    }
+}
+
+static uint64_t read_mpidr(void)
+{
+    uint64_t mpidr;
+    __asm__ volatile("mrs %0, MPIDR_EL1" : "=r"(mpidr));
+    return mpidr & MPIDR_AFFINITY_MASK;
+}
+
+// ========================================
+// DISCOVERY
+// ========================================
+
+int aarch64_psci_init(const fdt_t *fdt)
+{
+    int node = fdt ? fdt_path_offset(fdt, "/psci") : -1;
+    const char *method = node >= 0 ? fdt_getprop_string(fdt, node, "method") : NULL;
+    if (!method)
+    {
+        kwarn("PSCI: no /psci node, secondary CPUs stay offline");
+        return -OR_ENODEV;
+    }
+    if (strcmp(method, "hvc") == 0)
+    {
+        g_psci_conduit = PSCI_CONDUIT_HVC;
+    }
+    else if (strcmp(method, "smc") == 0)
+    {
+        g_psci_conduit = PSCI_CONDUIT_SMC;
+    }
+    else
+    {
+        kerror("PSCI: unknown conduit %s", method);
+        return -OR_ENOTSUP;
+    }
+
+    // PSCI 0.1 firmware has no VERSION call and its own function ids
+    if (!fdt_node_is_compatible(fdt, node, "arm,psci-0.2") && !fdt_node_is_compatible(fdt, node, "arm,psci-1.0"))
+    {
+        kerror("PSCI: only PSCI 0.2 and later are supported");
+        g_psci_conduit = PSCI_CONDUIT_NONE;
+        return -OR_ENOTSUP;
+    }
+    g_psci_version = (uint32_t)psci_call(PSCI_0_2_FN_VERSION, 0, 0, 0);
+    kinfo("PSCI: version %u.%u over %s", g_psci_version >> 16, g_psci_version & 0xFFFF, method);
+    return OR_OK;
+}
+
+// Number the CPUs of /cpus, the boot CPU first
+int aarch64_cpus_init(const fdt_t *fdt)
+{
+    uint64_t boot_mpidr = read_mpidr();
+    g_cpu_mpidr[0] = boot_mpidr;
+    g_cpu_count = 1;
+    g_cpu_online[0] = true;
+
+    int cpus = fdt ? fdt_path_offset(fdt, "/cpus") : -1;
+    int first = cpus >= 0 ? fdt_first_subnode(fdt, cpus) : -1;
+    for (int node = first; node >= 0; node = fdt_next_subnode(fdt, node))
+    {
+        const char *type = fdt_getprop_string(fdt, node, "device_type");
+        uint64_t mpidr;
+        if (!type || strcmp(type, "cpu") != 0 || !fdt_node_is_enabled(fdt, node) ||
+            fdt_read_reg(fdt, node, 0, &mpidr, NULL) != OR_OK)
+        {
+            continue;
+        }
+        mpidr &= MPIDR_AFFINITY_MASK;
+        if (mpidr == boot_mpidr)
+        {
+            continue;
+        }
+        if (g_cpu_count == AARCH64_MAX_CPUS)
+        {
+            kwarn("aarch64: more than %u CPUs, ignoring the rest", AARCH64_MAX_CPUS);
+            break;
+        }
+        g_cpu_mpidr[g_cpu_count++] = mpidr;
+    }
+
+    kinfo("aarch64: %u CPUs, boot CPU MPIDR 0x%llx", g_cpu_count, (unsigned long long)boot_mpidr);
+    return (int)g_cpu_count;
+}
+
+uint32_t aarch64_cpu_count(void)
+{
+    return g_cpu_count;
+}
+
+uint64_t aarch64_cpu_mpidr(uint32_t cpu)
+{
+    return cpu < g_cpu_count ? g_cpu_mpidr[cpu] : 0;
+}
+
+uint32_t arch_get_cpu_count(void)
+{
+    return g_cpu_count;
+}
+
+bool aarch64_cpu_online(uint32_t cpu)
+{
+    return cpu < g_cpu_count && g_cpu_online[cpu];
+}
+
+uint32_t arch_get_current_cpu(void)
+{
+    uint64_t cpu;
+    __asm__ volatile("mrs %0, TPIDR_EL1" : "=r"(cpu));
+    return (uint32_t)cpu;
+}
+
+// ========================================
+// CPU BRING-UP
+// ========================================
+
+int aarch64_psci_cpu_on(uint32_t cpu)
+{
+    if (cpu == 0 || cpu >= g_cpu_count)
+    {
+        return -OR_EINVAL;
+    }
+    aarch64_cpu_stack_top[cpu] = (uint64_t)(uintptr_t)(g_secondary_stacks[cpu] + AARCH64_SECONDARY_STACK_SIZE);
+    // The new CPU starts with its caches off and must see the stack top
+    __asm__ volatile("dc civac, %0\n"
+                     "dsb ish" ::"r"(&aarch64_cpu_stack_top[cpu])
+                     : "memory");
+
+    int64_t result = psci_call(PSCI_0_2_FN64_CPU_ON, g_cpu_mpidr[cpu], (uint64_t)(uintptr_t)aarch64_secondary_entry,
+                               cpu);
+    return psci_to_error(result);
+}
+
+// Entered by each secondary CPU once on its own stack at EL1; never returns
+void aarch64_secondary_main(uint64_t cpu)
+{
+    __asm__ volatile("msr TPIDR_EL1, %0" ::"r"(cpu));
+    aarch64_mmu_init();
+    irq_cpu_init((uint32_t)cpu);
+    aarch64_timer_cpu_init();
+
+    g_cpu_online[cpu] = true;
+    __asm__ volatile("dsb ish" ::: "memory");
+    kinfo("aarch64: CPU %llu online (MPIDR 0x%llx)", (unsigned long long)cpu,
+          (unsigned long long)g_cpu_mpidr[cpu]);
+
+    smp_secondary_main((uint32_t)cpu);
+}
+
+int arch_smp_boot(void)
+{
+    if (g_psci_conduit == PSCI_CONDUIT_NONE)
+    {
+        return 1;
+    }
+
+    uint32_t online = 1;
+    for (uint32_t cpu = 1; cpu < g_cpu_count; cpu++)
+    {
+        int result = aarch64_psci_cpu_on(cpu);
+        if (result != OR_OK)
+        {
+            kerror("aarch64: CPU_ON for CPU %u failed: %d", cpu, result);
+            continue;
+        }
+        for (uint64_t loops = 0; !g_cpu_online[cpu] && loops < AARCH64_CPU_ON_TIMEOUT_LOOPS; loops++)
+        {
+            arch_pause();
+        }
+        if (g_cpu_online[cpu])
+        {
+            online++;
+        }
+        else
+        {
+            kerror("aarch64: CPU %u did not come online", cpu);
+        }
+    }
+    kinfo("aarch64: %u of %u CPUs online", online, g_cpu_count);
+    return (int)online;
+}
+
+void aarch64_psci_system_off(void)
+{
+    psci_call(PSCI_0_2_FN_SYSTEM_OFF, 0, 0, 0);
+}
+
+void aarch64_psci_system_reset(void)
+{
+    psci_call(PSCI_0_2_FN_SYSTEM_RESET, 0, 0, 0);
+}
@@ -22,6 +22,7 @@
 #include "hal_common.h"
 #include <orion/kernel.h>
 #include <orion/types.h>
+#include <orion/irq.h>
 #include <arch.h> // Include existing aarch64 architecture definitions
 
 // External functions from kernel/arch/aarch64
@@ -190,38 +191,33 @@ int hal_aarch64_interrupt_init(void)
 int hal_aarch64_irq_register(uint32_t irq, irq_handler_t handler, void *data)
 {
     kinfo("HAL: Registering AArch64 IRQ %u handler", irq);
-    // Use existing kernel/arch function
-    // return arch_irq_register(irq, handler, data);  // Defined in kernel/arch/aarch64/interrupts.c
-    return ORION_HAL_SUCCESS;
+    // Routed through the kernel's orion-irq layer and the GICv3 chip
+    return irq_request(irq, handler, data, "hal");
 }
 
 int hal_aarch64_irq_unregister(uint32_t irq)
 {
     kinfo("HAL: Unregistering AArch64 IRQ %u", irq);
-    // Use existing kernel/arch function
-    // return arch_irq_unregister(irq);  // Defined in kernel/arch/aarch64/interrupts.c
-    return ORION_HAL_SUCCESS;
+    return irq_free(irq);
 }
 
 void hal_aarch64_irq_enable(uint32_t irq)
 {
     kinfo("HAL: Enabling AArch64 IRQ %u", irq);
-    // Use existing kernel/arch function
-    // arch_irq_enable(irq);  // Defined in kernel/arch/aarch64/interrupts.c
+    irq_enable(irq);
 }
 
 void hal_aarch64_irq_disable(uint32_t irq)
 {
     kinfo("HAL: Disabling AArch64 IRQ %u", irq);
-    // Use existing kernel/arch function
-    // arch_irq_disable(irq);  // Defined in kernel/arch/aarch64/interrupts.c
+    irq_disable(irq);
 }
 
 void hal_aarch64_irq_ack(uint32_t irq)
 {
     kinfo("HAL: Acknowledging AArch64 IRQ %u", irq);
-    // Use existing kernel/arch function
-    // arch_irq_ack(irq);  // Defined in kernel/arch/aarch64/interrupts.c
+    // The GICv3 chip acknowledges and completes interrupts in irq_handle()
+    (void)irq;
 }
 
 int hal_aarch64_cpu_init(uint32_t cpu_id)
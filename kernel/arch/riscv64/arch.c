@@ -13,6 +13,8 @@
 #include "arch.h"
 #include <stdio.h>
 #include <string.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
 #include <stdint.h>
 #include <stdbool.h>
 
@@ -32,16 +34,31 @@ void riscv64_detect_cpu_features(void)
 {
     printf("RISC-V: Detecting CPU features...\n");
 
-    // Read MISA CSR to detect base ISA and extensions
-    uint64_t misa;
-    __asm__ volatile("csrr %0, %1" : "=r"(misa) : "i"(RISCV64_CSR_MISA));
-
-    // Extract base ISA (bits 62-30)
-    uint64_t base_isa = (misa >> 30) & 0x3F;
+    // misa is an M-mode CSR: under SBI the extensions come from the boot
+    // hart's "riscv,isa" string, e.g. "rv64imafdc_zicsr_zifencei"
+    uint64_t base_isa = 2; // XLEN 64
+    uint64_t extensions = 0;
+    const fdt_t *fdt = fdt_boot();
+    int cpus = fdt ? fdt_path_offset(fdt, "/cpus") : -1;
+    int cpu = cpus >= 0 ? fdt_first_subnode(fdt, cpus) : -1;
+    const char *isa = cpu >= 0 ? fdt_getprop_string(fdt, cpu, "riscv,isa") : NULL;
+    if (isa && strncmp(isa, "rv64", 4) == 0)
+    {
+        for (const char *p = isa + 4; *p && *p != '_'; p++)
+        {
+            if (*p >= 'a' && *p <= 'z')
+            {
+                extensions |= 1ULL << (*p - 'a');
+            }
+        }
+        if (extensions & (1ULL << ('g' - 'a')))
+        {
+            // G stands for IMAFD plus Zicsr and Zifencei
+            extensions |= (1ULL << ('i' - 'a')) | (1ULL << ('m' - 'a')) | (1ULL << ('a' - 'a')) |
+                          (1ULL << ('f' - 'a')) | (1ULL << ('d' - 'a'));
+        }
+    }
     riscv64_cpu_family = (uint32_t)base_isa;
-
-    // Extract extensions (bits 25-0)
-    uint64_t extensions = misa & 0x3FFFFFF;
     riscv64_cpu_features = extensions;
 
     // Set feature flags
@@ -62,15 +79,13 @@ void riscv64_detect_cpu_features(void)
     if (extensions & (1 << ('B' - 'A')))
         riscv64_cpu_features |= RISCV64_FEATURE_RV64B;
 
-    // Read vendor and implementation information
-    uint64_t mvendorid, marchid, mimpid, mhartid;
-    __asm__ volatile("csrr %0, %1" : "=r"(mvendorid) : "i"(0xF11));
-    __asm__ volatile("csrr %0, %1" : "=r"(marchid) : "i"(0xF12));
-    __asm__ volatile("csrr %0, %1" : "=r"(mimpid) : "i"(0xF13));
-    __asm__ volatile("csrr %0, %1" : "=r"(mhartid) : "i"(RISCV64_CSR_MHARTID));
+    // Vendor and implementation registers are read by the firmware for us
+    uint64_t mvendorid, marchid, mimpid;
+    uint64_t mhartid = riscv64_cpu_hartid(arch_get_current_cpu());
+    riscv64_sbi_get_ids(&mvendorid, &marchid, &mimpid);
 
     printf("RISC-V: CPU features detected:\n");
-    printf("  Base ISA: RV64%c\n", (char)(base_isa + 'A'));
+    printf("  Base ISA: %s\n", isa ? isa : "rv64 (unknown extensions)");
     printf("  Extensions: ");
 
     if (extensions & (1 << ('I' - 'A')))
@@ -110,145 +125,37 @@ void riscv64_print_cpu_info(void)
     printf("  Features: 0x%llx\n", (unsigned long long)riscv64_cpu_features);
 }
 
-// ============================================================================
-// MMU MANAGEMENT
-// ============================================================================
-
-void riscv64_mmu_init(void)
-{
-    printf("RISC-V: Initializing MMU...\n");
-
-    // Set up page table base
-    uint64_t satp = 0; // Disable paging initially
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_SATP), "r"(satp));
-
-    // Configure PMP (Physical Memory Protection) if available
-    if (riscv64_has_feature(RISCV64_FEATURE_RV64A))
-    {
-        // Set up PMP to allow all memory access
-        uint64_t pmpcfg = 0x0F0F0F0F0F0F0F0FULL; // TOR, R/W/X for all regions
-        __asm__ volatile("csrw %0, %1" : : "i"(0x3A0), "r"(pmpcfg));
-
-        // Set PMP address registers
-        uint64_t pmpaddr = 0x3FFFFFFFFFFFFFFFULL; // Cover all 64-bit address space
-        for (int i = 0; i < 8; i++)
-        {
-            uint32_t csr_addr = 0x3B0 + i;
-            __asm__ volatile("csrw %0, %1" : : "i"(csr_addr), "r"(pmpaddr));
-        }
-    }
-
-    printf("RISC-V: MMU initialized\n");
-}
-
-int riscv64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags)
-{
-    // This is a simplified implementation
-    // In a real system, this would walk the page table and create entries
-    printf("RISC-V: Mapping page 0x%llx -> 0x%llx (flags: 0x%llx)\n",
-           (unsigned long long)va, (unsigned long long)pa, (unsigned long long)flags);
-    return 0;
-}
-
-int riscv64_mmu_unmap_page(uint64_t va)
-{
-    // This is a simplified implementation
-    printf("RISC-V: Unmapping page 0x%llx\n", (unsigned long long)va);
-    return 0;
-}
-
-void riscv64_mmu_invalidate_tlb(void)
-{
-    // Invalidate TLB by writing to satp
-    uint64_t satp;
-    __asm__ volatile("csrr %0, %1" : "=r"(satp) : "i"(RISCV64_CSR_SATP));
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_SATP), "r"(satp));
-    printf("RISC-V: TLB invalidated\n");
-}
-
 // ============================================================================
 // INTERRUPT MANAGEMENT
 // ============================================================================
 
-void riscv64_interrupts_init(void)
-{
-    printf("RISC-V: Initializing interrupts...\n");
-
-    // Set up trap vector base
-    extern void riscv64_trap_vector(void);
-    uint64_t trap_vector = (uint64_t)riscv64_trap_vector;
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_MTVEC), "r"(trap_vector));
-
-    // Enable machine mode interrupts
-    uint64_t mie = (1ULL << RISCV64_IRQ_SOFTWARE) |
-                   (1ULL << RISCV64_IRQ_TIMER) |
-                   (1ULL << RISCV64_IRQ_EXTERNAL);
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_MIE), "r"(mie));
-
-    // Enable machine mode interrupts globally
-    uint64_t mstatus;
-    __asm__ volatile("csrr %0, %1" : "=r"(mstatus) : "i"(RISCV64_CSR_MSTATUS));
-    mstatus |= (1ULL << 3); // MIE bit
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_MSTATUS), "r"(mstatus));
-
-    printf("RISC-V: Interrupts initialized\n");
-}
+// Paging lives in mmu.c, the PLIC in plic.c and the timer in clint.c
 
 void riscv64_interrupt_enable(uint32_t irq)
 {
-    uint64_t mie;
-    __asm__ volatile("csrr %0, %1" : "=r"(mie) : "i"(RISCV64_CSR_MIE));
-    mie |= (1ULL << irq);
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_MIE), "r"(mie));
+    irq_enable(irq);
 }
 
 void riscv64_interrupt_disable(uint32_t irq)
 {
-    uint64_t mie;
-    __asm__ volatile("csrr %0, %1" : "=r"(mie) : "i"(RISCV64_CSR_MIE));
-    mie &= ~(1ULL << irq);
-    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_MIE), "r"(mie));
+    irq_disable(irq);
 }
 
-void riscv64_interrupt_set_handler(uint32_t irq, void (*handler)(void))
+static void riscv64_legacy_irq(uint32_t irq, void *data)
 {
-    // In a real implementation, this would set up interrupt handlers
-    printf("RISC-V: Setting interrupt handler for IRQ %u\n", irq);
+    (void)irq;
+    ((void (*)(void))data)();
 }
 
-// ============================================================================
-// TIMER MANAGEMENT
-// ============================================================================
-
-void riscv64_timer_init(void)
-{
-    printf("RISC-V: Initializing timer...\n");
-
-    // Read time CSR to get current time
-    uint64_t time;
-    __asm__ volatile("csrr %0, %1" : "=r"(time) : "i"(0xC01));
-
-    // Set up timer interrupt
-    uint64_t mtimecmp = time + 1000000; // 1 second from now
-    __asm__ volatile("csrw %0, %1" : : "i"(0x321), "r"(mtimecmp));
-
-    printf("RISC-V: Timer initialized at %llu\n", (unsigned long long)time);
-}
-
-uint64_t riscv64_timer_read_ns(void)
-{
-    uint64_t time;
-    __asm__ volatile("csrr %0, %1" : "=r"(time) : "i"(0xC01));
-
-    // Convert to nanoseconds (assuming 1MHz timer)
-    return time * 1000;
-}
-
-int riscv64_timer_set_oneshot(uint64_t deadline_ns)
+void riscv64_interrupt_set_handler(uint32_t irq, void (*handler)(void))
 {
-    uint64_t deadline_cycles = deadline_ns / 1000; // Convert to cycles
-    __asm__ volatile("csrw %0, %1" : : "i"(0x321), "r"(deadline_cycles));
-    return 0;
+    // Handlers without arguments are registered with the orion-irq layer
+    // through a trampoline
+    irq_free(irq);
+    if (handler && irq_request(irq, riscv64_legacy_irq, (void *)handler, "riscv64") != 0)
+    {
+        printf("RISC-V: Cannot set handler for IRQ %u\n", irq);
+    }
 }
 
 // ============================================================================
@@ -333,9 +240,10 @@ void riscv64_pmu_init(void)
 {
     printf("RISC-V: Initializing performance monitoring...\n");
 
-    // Enable performance counters
-    uint64_t mcounteren = 0xFFFFFFFFFFFFFFFFULL;
-    __asm__ volatile("csrw %0, %1" : : "i"(0x306), "r"(mcounteren));
+    // Let U-mode read cycle, time and instret; the firmware grants them
+    // to S-mode through mcounteren
+    uint64_t scounteren = 0x7;
+    __asm__ volatile("csrw %0, %1" : : "i"(RISCV64_CSR_SCOUNTEREN), "r"(scounteren));
 
     printf("RISC-V: Performance monitoring initialized\n");
 }
@@ -356,11 +264,7 @@ uint64_t riscv64_pmu_read_counter(uint32_t counter)
         __asm__ volatile("csrr %0, %1" : "=r"(value) : "i"(0xC02)); // instret
         break;
     default:
-        if (counter >= 3 && counter <= 31)
-        {
-            uint32_t csr_addr = 0xB00 + counter;
-            __asm__ volatile("csrr %0, %1" : "=r"(value) : "i"(csr_addr));
-        }
+        // hpmcounters need the SBI PMU extension to be programmed
         break;
     }
 
@@ -369,11 +273,10 @@ uint64_t riscv64_pmu_read_counter(uint32_t counter)
 
 void riscv64_pmu_set_event(uint32_t counter, uint32_t event)
 {
-    // Set performance counter event type
-    if (counter >= 3 && counter <= 31)
-    {
-        __asm__ volatile("csrw %0, %1" : : "i"(0x320 + counter), "r"((uint64_t)event));
-    }
+    // mhpmevent registers belong to M-mode; event selection needs the SBI
+    // PMU extension, which is not used yet
+    (void)counter;
+    (void)event;
 }
 
 // ============================================================================
@@ -442,10 +345,9 @@ void riscv64_arch_init(void)
     // Detect CPU features first
     riscv64_detect_cpu_features();
 
-    // Initialize subsystems
-    riscv64_mmu_init();
-    riscv64_interrupts_init();
-    riscv64_timer_init();
+    // Initialize subsystems. Paging is already on (riscv64_boot_main);
+    // the PLIC and the timer follow from arch_interrupt_init and
+    // arch_timer_init once the kernel's interrupt layer exists
     riscv64_cache_init();
     riscv64_vector_init();
     riscv64_security_init();
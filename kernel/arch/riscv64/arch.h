@@ -17,6 +17,7 @@
 #include <stdint.h>
 #include <stdbool.h>
 #include "config.h"
+#include <orion/fdt.h>
 
 // ============================================================================
 // ARCHITECTURE IDENTIFICATION
@@ -195,9 +196,13 @@ bool riscv64_has_feature(uint64_t feature);
 void riscv64_print_cpu_info(void);
 
 // MMU management
+int riscv64_mmu_setup(const fdt_t *fdt, const fdt_region_t *ram, int count);
 void riscv64_mmu_init(void);
+bool riscv64_mmu_enabled(void);
+void riscv64_mmu_disable(void);
 int riscv64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags);
 int riscv64_mmu_unmap_page(uint64_t va);
+uint64_t riscv64_mmu_translate(uint64_t va);
 void riscv64_mmu_invalidate_tlb(void);
 
 // Interrupt management
@@ -205,9 +210,28 @@ void riscv64_interrupts_init(void);
 void riscv64_interrupt_enable(uint32_t irq);
 void riscv64_interrupt_disable(uint32_t irq);
 void riscv64_interrupt_set_handler(uint32_t irq, void (*handler)(void));
+int riscv64_plic_init(const fdt_t *fdt);
+
+// SBI and hart bring-up
+int riscv64_sbi_init(void);
+void riscv64_sbi_get_ids(uint64_t *mvendorid, uint64_t *marchid, uint64_t *mimpid);
+void riscv64_sbi_set_timer(uint64_t stime_value);
+int riscv64_sbi_send_ipi(unsigned long hart_mask, unsigned long hart_mask_base);
+void riscv64_sbi_system_off(void);
+void riscv64_sbi_system_reset(void);
+int riscv64_cpus_init(const fdt_t *fdt, uint64_t boot_hartid);
+uint32_t riscv64_cpu_count(void);
+uint64_t riscv64_cpu_hartid(uint32_t cpu);
+int riscv64_hartid_to_cpu(uint64_t hartid);
+bool riscv64_cpu_online(uint32_t cpu);
+int riscv64_hart_start(uint32_t cpu);
+void riscv64_secondary_main(uint64_t cpu);
 
 // Timer management
 void riscv64_timer_init(void);
+void riscv64_timer_cpu_init(void);
+void riscv64_timer_interrupt(void);
+uint64_t riscv64_timer_get_frequency(void);
 uint64_t riscv64_timer_read_ns(void);
 int riscv64_timer_set_oneshot(uint64_t deadline_ns);
 
@@ -240,11 +264,13 @@ uint32_t riscv64_numa_get_node_count(void);
 uint32_t riscv64_numa_get_current_node(void);
 
 // Main initialization
+void riscv64_boot_main(uint64_t hartid, uint64_t dtb);
 void riscv64_arch_init(void);
 
 // Exception handlers (C functions called from assembly)
 void riscv64_sync_exception_handler(uint64_t cause, uint64_t epc, uint64_t tval);
 void riscv64_interrupt_handler(void);
+void riscv64_supervisor_trap_handler_c(uint64_t cause, uint64_t epc, uint64_t tval);
 void riscv64_syscall_dispatcher(void);
 
 // External variables
@@ -0,0 +1,118 @@
+/*
+ * ORION OS - RISC-V 64-bit CLINT Timer
+ *
+ * Periodic tick and clock on the core-local interruptor. Its mtime
+ * counter is read from S-mode through the time CSR; its comparators are
+ * M-mode registers behind the firmware's PMP, so deadlines are programmed
+ * with the SBI TIME extension and arrive as supervisor timer interrupts.
+ * The counter frequency is the "timebase-frequency" of /cpus (10 MHz on
+ * QEMU virt). Timer interrupts are per hart and do not go through the
+ * PLIC, so the trap handler calls riscv64_timer_interrupt directly.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/fdt.h>
+#include "arch.h"
+
+extern void scheduler_tick(void);
+
+static uint64_t g_timebase_frequency = 0;
+static uint64_t g_tick_period = 0;
+static uint64_t g_next_tick[RISCV64_SMP_MAX_HARTS];
+
+static inline uint64_t clint_read_time(void)
+{
+    uint64_t time;
+    __asm__ volatile("rdtime %0" : "=r"(time));
+    return time;
+}
+
+static uint64_t clint_timebase_frequency(const fdt_t *fdt)
+{
+    int cpus = fdt ? fdt_path_offset(fdt, "/cpus") : -1;
+    uint32_t frequency;
+    if (cpus >= 0 && fdt_getprop_u32(fdt, cpus, "timebase-frequency", &frequency) && frequency)
+    {
+        return frequency;
+    }
+    // Some trees only give it on each cpu node
+    int first = cpus >= 0 ? fdt_first_subnode(fdt, cpus) : -1;
+    if (first >= 0 && fdt_getprop_u32(fdt, first, "timebase-frequency", &frequency) && frequency)
+    {
+        return frequency;
+    }
+    return RISCV64_TIMER_FREQ_DEFAULT;
+}
+
+// Arm the periodic tick on the running hart
+void riscv64_timer_cpu_init(void)
+{
+    if (!g_tick_period)
+    {
+        return;
+    }
+    uint32_t cpu = arch_get_current_cpu();
+    g_next_tick[cpu] = clint_read_time() + g_tick_period;
+    riscv64_sbi_set_timer(g_next_tick[cpu]);
+    __asm__ volatile("csrs sie, %0" ::"r"(RISCV64_SIE_STIE) : "memory");
+}
+
+void riscv64_timer_init(void)
+{
+    g_timebase_frequency = clint_timebase_frequency(fdt_boot());
+    g_tick_period = g_timebase_frequency / RISCV64_TIMER_TICK_HZ;
+    riscv64_timer_cpu_init();
+    kinfo("CLINT: timebase %llu Hz, %u Hz tick", (unsigned long long)g_timebase_frequency, RISCV64_TIMER_TICK_HZ);
+}
+
+// Supervisor timer interrupt: schedule the next tick from the previous
+// deadline so the period does not drift with interrupt latency
+void riscv64_timer_interrupt(void)
+{
+    uint32_t cpu = arch_get_current_cpu();
+    uint64_t now = clint_read_time();
+    do
+    {
+        g_next_tick[cpu] += g_tick_period;
+    } while (g_next_tick[cpu] <= now);
+    riscv64_sbi_set_timer(g_next_tick[cpu]);
+    scheduler_tick();
+}
+
+uint64_t riscv64_timer_get_frequency(void)
+{
+    return g_timebase_frequency;
+}
+
+uint64_t riscv64_timer_read_ns(void)
+{
+    if (!g_timebase_frequency)
+    {
+        return 0;
+    }
+    uint64_t time = clint_read_time();
+
+    // Split to avoid overflowing the 64-bit product
+    return (time / g_timebase_frequency) * 1000000000ULL +
+           ((time % g_timebase_frequency) * 1000000000ULL) / g_timebase_frequency;
+}
+
+int riscv64_timer_set_oneshot(uint64_t deadline_ns)
+{
+    if (!g_timebase_frequency)
+    {
+        return -OR_EINVAL;
+    }
+    uint64_t ticks = (deadline_ns / 1000000000ULL) * g_timebase_frequency +
+                     ((deadline_ns % 1000000000ULL) * g_timebase_frequency) / 1000000000ULL;
+    g_next_tick[arch_get_current_cpu()] = ticks;
+    riscv64_sbi_set_timer(ticks);
+    return OR_OK;
+}
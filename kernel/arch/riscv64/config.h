@@ -115,6 +115,12 @@
 #define RISCV64_CSR_SIP 0x144
 #define RISCV64_CSR_SATP 0x180
 
+/* sstatus, sie and sip bits */
+#define RISCV64_SSTATUS_SIE (1ULL << 1)
+#define RISCV64_SIE_SSIE (1ULL << 1)
+#define RISCV64_SIE_STIE (1ULL << 5)
+#define RISCV64_SIE_SEIE (1ULL << 9)
+
 // ============================================================================
 // TIMER SYSTEM
 // ============================================================================
@@ -127,6 +133,8 @@
 /* CLINT Configuration */
 #define RISCV64_CLINT_BASE 0x2000000
 #define RISCV64_CLINT_SIZE 0x10000
+#define RISCV64_TIMER_FREQ_DEFAULT 10000000ULL /* QEMU virt timebase */
+#define RISCV64_TIMER_TICK_HZ 100
 
 // ============================================================================
 // CACHE CONFIGURATION
@@ -212,6 +220,7 @@
 
 #define RISCV64_MAX_IRQS 1024
 #define RISCV64_MAX_CPUS 256
+#define RISCV64_SMP_MAX_HARTS 8 /* Harts brought up through SBI HSM */
 #define RISCV64_MAX_NODES 16
 #define RISCV64_MAX_VMS 64
 #define RISCV64_MAX_PROCESSES 32768
@@ -1,7 +1,17 @@
 /*
  * ORION OS - RISC-V 64-bit Memory Management Unit
  *
- * Memory Management Unit for RISC-V 64-bit architecture
+ * Supervisor address translation with Sv39 (three levels) or Sv48 (four
+ * levels) of 512-entry tables behind satp, whichever the boot hart's
+ * "mmu-type" in the device tree allows. The kernel runs identity-mapped:
+ * RAM reported by the device tree is mapped with gigapages and megapages
+ * where alignment allows, and the device window below it (CLINT, PLIC,
+ * UART, virtio-mmio on QEMU virt) read-write and never executable. Without
+ * Svpbmt the platform's PMAs, not the page tables, make that window
+ * uncached. Superpages are split when a page inside them is remapped or
+ * unmapped.
+ *
+ * Table pages come from a static pool until the physical allocator is up.
  *
  * Developed by Jeremy Noverraz (1988-2025)
  * August 2025, Lausanne, Switzerland
@@ -10,704 +20,402 @@
  * License: MIT
  */
 
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/fdt.h>
+#include <orion/smp.h>
 #include "arch.h"
-#include <stdint.h>
-#include <stdbool.h>
-#include <stddef.h>
-
-// ============================================================================
-// GLOBAL MMU CONTEXTS
-// ============================================================================
-
-// MMU context
-static struct {
-    bool initialized;
-    uint64_t satp;  // Supervisor Address Translation and Protection
-    uint64_t asid;  // Address Space ID
-    uint64_t *root_page_table;
-    uint64_t page_table_count;
-    uint64_t total_pages;
-    uint64_t used_pages;
-} riscv64_mmu_context = {0};
-
-// TLB context
-static struct {
-    bool initialized;
-    uint64_t tlb_entries[RISCV64_TLB_ENTRIES];
-    uint64_t tlb_tags[RISCV64_TLB_ENTRIES];
-    bool tlb_valid[RISCV64_TLB_ENTRIES];
-    uint32_t tlb_hits;
-    uint32_t tlb_misses;
-    uint32_t tlb_evictions;
-} riscv64_tlb_context = {0};
-
-// Page table context
-static struct {
-    bool initialized;
-    uint64_t *kernel_page_table;
-    uint64_t *user_page_table;
-    uint64_t page_table_pages;
-    uint64_t page_table_size;
-} riscv64_page_table_context = {0};
-
-// Memory region context
-static struct {
-    bool initialized;
-    uint64_t regions[64];
-    uint64_t region_flags[64];
-    uint64_t region_count;
-} riscv64_memory_regions = {0};
-
-// ============================================================================
-// MMU INITIALIZATION
-// ============================================================================
-
-// Initialize MMU system
-void riscv64_mmu_init(void)
-{
-    if (riscv64_mmu_context.initialized) {
-        return;
-    }
-    
-    // Initialize MMU context
-    riscv64_mmu_context.satp = 0;
-    riscv64_mmu_context.asid = 0;
-    riscv64_mmu_context.root_page_table = NULL;
-    riscv64_mmu_context.page_table_count = 0;
-    riscv64_mmu_context.total_pages = 0;
-    riscv64_mmu_context.used_pages = 0;
-    riscv64_mmu_context.initialized = true;
-    
-    // Initialize TLB context
-    for (int i = 0; i < RISCV64_TLB_ENTRIES; i++) {
-        riscv64_tlb_context.tlb_entries[i] = 0;
-        riscv64_tlb_context.tlb_tags[i] = 0;
-        riscv64_tlb_context.tlb_valid[i] = false;
-    }
-    riscv64_tlb_context.tlb_hits = 0;
-    riscv64_tlb_context.tlb_misses = 0;
-    riscv64_tlb_context.tlb_evictions = 0;
-    riscv64_tlb_context.initialized = true;
-    
-    // Initialize page table context
-    riscv64_page_table_context.kernel_page_table = NULL;
-    riscv64_page_table_context.user_page_table = NULL;
-    riscv64_page_table_context.page_table_pages = 0;
-    riscv64_page_table_context.page_table_size = 0;
-    riscv64_page_table_context.initialized = true;
-    
-    // Initialize memory regions
-    riscv64_memory_regions.region_count = 0;
-    riscv64_memory_regions.initialized = true;
-    
-    // Create initial page tables
-    riscv64_create_initial_page_tables();
-    
-    printf("RISC-V64: MMU system initialized\n");
-}
 
-// ============================================================================
-// PAGE TABLE MANAGEMENT
-// ============================================================================
-
-// Create initial page tables
-void riscv64_create_initial_page_tables(void)
+// ========================================
+// PAGE TABLE ENTRIES
+// ========================================
+
+#define PTE_V (1ULL << 0)
+#define PTE_R (1ULL << 1)
+#define PTE_W (1ULL << 2)
+#define PTE_X (1ULL << 3)
+#define PTE_U (1ULL << 4)
+#define PTE_G (1ULL << 5)
+#define PTE_A (1ULL << 6)
+#define PTE_D (1ULL << 7)
+#define PTE_LEAF (PTE_R | PTE_W | PTE_X)
+#define PTE_PPN_SHIFT 10
+#define PTE_PPN_MASK 0x003FFFFFFFFFFC00ULL
+#define PTE_FLAGS_MASK 0x3FFULL
+
+#define PT_ENTRIES 512
+
+#define SATP_MODE_SV39 (8ULL << 60)
+#define SATP_MODE_SV48 (9ULL << 60)
+
+#define MMU_BOOT_POOL_PAGES 64
+
+// Physical window holding the platform devices on QEMU virt
+#define MMU_DEVICE_WINDOW_BASE 0x00000000ULL
+#define MMU_DEVICE_WINDOW_SIZE 0x80000000ULL
+
+static uint64_t g_boot_pool[MMU_BOOT_POOL_PAGES][PT_ENTRIES] __attribute__((aligned(RISCV64_PAGE_SIZE)));
+static uint32_t g_boot_pool_used = 0;
+static uint64_t *g_root_table = NULL;
+static int g_levels = 3;
+static uint64_t g_satp_mode = SATP_MODE_SV39;
+static spinlock_t g_mmu_lock = SPINLOCK_INIT;
+static bool g_mmu_enabled = false;
+
+static uint64_t *mmu_alloc_table(void)
 {
-    // Allocate kernel page table
-    riscv64_page_table_context.kernel_page_table = riscv64_allocate_page_table();
-    if (!riscv64_page_table_context.kernel_page_table) {
-        printf("RISC-V64: Failed to allocate kernel page table\n");
-        return;
+    uint64_t *table = NULL;
+    if (g_boot_pool_used < MMU_BOOT_POOL_PAGES)
+    {
+        table = g_boot_pool[g_boot_pool_used++];
     }
-    
-    // Allocate user page table
-    riscv64_page_table_context.user_page_table = riscv64_allocate_page_table();
-    if (!riscv64_page_table_context.user_page_table) {
-        printf("RISC-V64: Failed to allocate user page table\n");
-        return;
+    else
+    {
+        // Identity-mapped: the physical address is usable as is
+        table = (uint64_t *)(uintptr_t)pmm_alloc_page();
     }
-    
-    // Map kernel space
-    riscv64_map_kernel_space();
-    
-    // Map device memory
-    riscv64_map_device_memory();
-    
-    printf("RISC-V64: Initial page tables created\n");
-}
-
-// Allocate page table
-uint64_t* riscv64_allocate_page_table(void)
-{
-    // Allocate a 4KB page for the page table
-    uint64_t *page_table = (uint64_t*)0x1000;  // Placeholder address
-    
-    // Clear the page table
-    for (int i = 0; i < 512; i++) {
-        page_table[i] = 0;
+    if (table)
+    {
+        memset(table, 0, RISCV64_PAGE_SIZE);
     }
-    
-    riscv64_page_table_context.page_table_pages++;
-    riscv64_page_table_context.page_table_size += 4096;
-    
-    return page_table;
+    return table;
 }
 
-// Switch page table
-int riscv64_page_table_switch(uint64_t *new_page_table)
+static inline uint32_t mmu_shift(int level)
 {
-    if (!riscv64_mmu_context.initialized || !new_page_table) {
-        return -1;
-    }
-    
-    // Update SATP register
-    uint64_t new_satp = ((uint64_t)riscv64_mmu_context.asid << 44) | 
-                        ((uint64_t)new_page_table >> 12);
-    
-    riscv64_mmu_context.satp = new_satp;
-    riscv64_mmu_context.root_page_table = new_page_table;
-    
-    // Write to SATP CSR
-    asm volatile("csrw satp, %0" : : "r"(new_satp));
-    
-    // Flush TLB
-    riscv64_tlb_invalidate_all();
-    
-    printf("RISC-V64: Page table switched to 0x%llx\n", (uint64_t)new_page_table);
-    return 0;
+    return RISCV64_PAGE_SHIFT + 9 * (g_levels - 1 - level);
 }
 
-// Create page table
-uint64_t* riscv64_page_table_create(void)
+static inline uint32_t mmu_index(uint64_t va, int level)
 {
-    return riscv64_allocate_page_table();
+    return (va >> mmu_shift(level)) & (PT_ENTRIES - 1);
 }
 
-// Destroy page table
-void riscv64_page_table_destroy(uint64_t *page_table)
+static inline uint64_t mmu_level_size(int level)
 {
-    if (!page_table) {
-        return;
-    }
-    
-    // Free the page table memory
-    // In a real system, this would return memory to the allocator
-    
-    riscv64_page_table_context.page_table_pages--;
-    riscv64_page_table_context.page_table_size -= 4096;
-    
-    printf("RISC-V64: Page table destroyed\n");
+    return 1ULL << mmu_shift(level);
 }
 
-// ============================================================================
-// MEMORY MAPPING
-// ============================================================================
-
-// Map kernel space
-void riscv64_map_kernel_space(void)
+static inline uint64_t pte_to_pa(uint64_t pte)
 {
-    if (!riscv64_page_table_context.kernel_page_table) {
-        return;
-    }
-    
-    // Map kernel base address (0x8000000000000000) to physical memory
-    uint64_t kernel_base = RISCV64_KERNEL_BASE;
-    uint64_t kernel_size = RISCV64_KERNEL_SIZE;
-    
-    for (uint64_t addr = kernel_base; addr < kernel_base + kernel_size; addr += RISCV64_PAGE_SIZE) {
-        riscv64_mmu_map_page(addr, addr, RISCV64_PAGE_READ | RISCV64_PAGE_WRITE | RISCV64_PAGE_EXECUTE);
-    }
-    
-    printf("RISC-V64: Kernel space mapped\n");
+    return ((pte & PTE_PPN_MASK) >> PTE_PPN_SHIFT) << RISCV64_PAGE_SHIFT;
 }
 
-// Map device memory
-void riscv64_map_device_memory(void)
+static inline uint64_t pa_to_pte(uint64_t pa)
 {
-    if (!riscv64_page_table_context.kernel_page_table) {
-        return;
-    }
-    
-    // Map device memory region
-    uint64_t device_base = 0x1000000000000000;
-    uint64_t device_size = 0x40000000;
-    
-    for (uint64_t addr = device_base; addr < device_base + device_size; addr += RISCV64_PAGE_SIZE) {
-        riscv64_mmu_map_page(addr, addr, RISCV64_PAGE_READ | RISCV64_PAGE_WRITE);
-    }
-    
-    printf("RISC-V64: Device memory mapped\n");
+    return (pa >> RISCV64_PAGE_SHIFT) << PTE_PPN_SHIFT;
 }
 
-// Map page
-int riscv64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags)
+static uint64_t mmu_attributes(uint64_t flags)
 {
-    if (!riscv64_mmu_context.initialized) {
-        return -1;
-    }
-    
-    // Calculate page table indices
-    uint64_t level1_index = (va >> 30) & 0x1FF;
-    uint64_t level2_index = (va >> 21) & 0x1FF;
-    uint64_t level3_index = (va >> 12) & 0x1FF;
-    
-    // Get root page table
-    uint64_t *root_table = riscv64_mmu_context.root_page_table;
-    if (!root_table) {
-        return -1;
-    }
-    
-    // Level 1 (1GB pages)
-    if ((va & 0x3FFFFFFF) == 0 && (pa & 0x3FFFFFFF) == 0) {
-        // Check if level 1 entry exists
-        if (!(root_table[level1_index] & 0x1)) {
-            // Create level 1 entry
-            root_table[level1_index] = (pa >> 30) | flags | 0x1;
-        }
-        return 0;
+    // A and D are set up front: hardware may fault instead of updating them
+    uint64_t attr = PTE_V | PTE_R | PTE_A | PTE_D;
+    if (flags & PAGE_FLAG_WRITE)
+    {
+        attr |= PTE_W;
     }
-    
-    // Level 2 (2MB pages)
-    if ((va & 0x1FFFFF) == 0 && (pa & 0x1FFFFF) == 0) {
-        // Check if level 2 entry exists
-        if (!(root_table[level1_index] & 0x1)) {
-            // Create level 2 table
-            uint64_t *level2_table = riscv64_allocate_page_table();
-            if (!level2_table) {
-                return -1;
-            }
-            root_table[level1_index] = ((uint64_t)level2_table >> 12) | 0x1;
-        }
-        
-        uint64_t *level2_table = (uint64_t*)((root_table[level1_index] >> 12) << 12);
-        level2_table[level2_index] = (pa >> 21) | flags | 0x1;
-        return 0;
+    if ((flags & PAGE_FLAG_EXEC) && !(flags & PAGE_FLAG_NO_CACHE))
+    {
+        attr |= PTE_X;
     }
-    
-    // Level 3 (4KB pages)
-    // Check if level 2 entry exists
-    if (!(root_table[level1_index] & 0x1)) {
-        // Create level 2 table
-        uint64_t *level2_table = riscv64_allocate_page_table();
-        if (!level2_table) {
-            return -1;
-        }
-        root_table[level1_index] = ((uint64_t)level2_table >> 12) | 0x1;
+    if (flags & PAGE_FLAG_USER)
+    {
+        attr |= PTE_U;
     }
-    
-    uint64_t *level2_table = (uint64_t*)((root_table[level1_index] >> 12) << 12);
-    
-    // Check if level 3 entry exists
-    if (!(level2_table[level2_index] & 0x1)) {
-        // Create level 3 table
-        uint64_t *level3_table = riscv64_allocate_page_table();
-        if (!level3_table) {
-            return -1;
-        }
-        level2_table[level2_index] = ((uint64_t)level3_table >> 12) | 0x1;
+    else
+    {
+        attr |= PTE_G;
     }
-    
-    uint64_t *level3_table = (uint64_t*)((level2_table[level2_index] >> 12) << 12);
-    level3_table[level3_index] = (pa >> 12) | flags | 0x1;
-    
-    // Invalidate TLB entry
-    riscv64_tlb_invalidate_entry(va);
-    
-    riscv64_mmu_context.used_pages++;
-    return 0;
+    return attr;
 }
 
-// Unmap page
-int riscv64_mmu_unmap_page(uint64_t va)
+static inline void mmu_flush_page(uint64_t va)
 {
-    if (!riscv64_mmu_context.initialized) {
-        return -1;
-    }
-    
-    // Calculate page table indices
-    uint64_t level1_index = (va >> 30) & 0x1FF;
-    uint64_t level2_index = (va >> 21) & 0x1FF;
-    uint64_t level3_index = (va >> 12) & 0x1FF;
-    
-    // Get root page table
-    uint64_t *root_table = riscv64_mmu_context.root_page_table;
-    if (!root_table) {
-        return -1;
-    }
-    
-    // Check if level 1 entry exists
-    if (!(root_table[level1_index] & 0x1)) {
-        return -1;  // Page not mapped
-    }
-    
-    // Level 1 (1GB pages)
-    if ((va & 0x3FFFFFFF) == 0) {
-        root_table[level1_index] = 0;
-        riscv64_tlb_invalidate_entry(va);
-        riscv64_mmu_context.used_pages--;
-        return 0;
-    }
-    
-    uint64_t *level2_table = (uint64_t*)((root_table[level1_index] >> 12) << 12);
-    
-    // Check if level 2 entry exists
-    if (!(level2_table[level2_index] & 0x1)) {
-        return -1;  // Page not mapped
-    }
-    
-    // Level 2 (2MB pages)
-    if ((va & 0x1FFFFF) == 0) {
-        level2_table[level2_index] = 0;
-        riscv64_tlb_invalidate_entry(va);
-        riscv64_mmu_context.used_pages--;
-        return 0;
-    }
-    
-    uint64_t *level3_table = (uint64_t*)((level2_table[level2_index] >> 12) << 12);
-    
-    // Check if level 3 entry exists
-    if (!(level3_table[level3_index] & 0x1)) {
-        return -1;  // Page not mapped
-    }
-    
-    // Level 3 (4KB pages)
-    level3_table[level3_index] = 0;
-    riscv64_tlb_invalidate_entry(va);
-    riscv64_mmu_context.used_pages--;
-    
-    return 0;
+    __asm__ volatile("sfence.vma %0, zero" ::"r"(va) : "memory");
 }
 
-// ============================================================================
-// TLB MANAGEMENT
-// ============================================================================
+// Replace the superpage at `entry` by a table of smaller leaves with the
+// same permissions
+static int mmu_split_leaf(uint64_t *entry, int level)
+{
+    uint64_t *table = mmu_alloc_table();
+    if (!table)
+    {
+        return -OR_ENOMEM;
+    }
+    uint64_t base = pte_to_pa(*entry);
+    uint64_t attr = *entry & PTE_FLAGS_MASK;
+    uint64_t step = mmu_level_size(level + 1);
+    for (uint32_t i = 0; i < PT_ENTRIES; i++)
+    {
+        table[i] = pa_to_pte(base + i * step) | attr;
+    }
+    __asm__ volatile("fence rw, rw" ::: "memory");
+    *entry = pa_to_pte((uint64_t)(uintptr_t)table) | PTE_V;
+    return OR_OK;
+}
 
-// Invalidate TLB entry
-void riscv64_tlb_invalidate_entry(uint64_t va)
+// Entry describing `va` at `level`, creating or splitting the tables
+// above it when `create` is set
+static uint64_t *mmu_walk(uint64_t va, int level, bool create)
 {
-    if (!riscv64_tlb_context.initialized) {
-        return;
-    }
-    
-    // Find TLB entry for this virtual address
-    uint64_t tag = va >> 12;  // Page number
-    
-    for (int i = 0; i < RISCV64_TLB_ENTRIES; i++) {
-        if (riscv64_tlb_context.tlb_valid[i] && riscv64_tlb_context.tlb_tags[i] == tag) {
-            riscv64_tlb_context.tlb_valid[i] = false;
-            riscv64_tlb_context.tlb_entries[i] = 0;
-            riscv64_tlb_context.tlb_tags[i] = 0;
-            break;
+    uint64_t *table = g_root_table;
+    for (int current = 0; current < level; current++)
+    {
+        uint64_t *entry = &table[mmu_index(va, current)];
+        if (!(*entry & PTE_V))
+        {
+            if (!create)
+            {
+                return NULL;
+            }
+            uint64_t *next = mmu_alloc_table();
+            if (!next)
+            {
+                return NULL;
+            }
+            *entry = pa_to_pte((uint64_t)(uintptr_t)next) | PTE_V;
         }
+        else if (*entry & PTE_LEAF)
+        {
+            if (!create || mmu_split_leaf(entry, current) != OR_OK)
+            {
+                return NULL;
+            }
+        }
+        table = (uint64_t *)(uintptr_t)pte_to_pa(*entry);
     }
-    
-    // Hardware TLB invalidation
-    asm volatile("sfence.vma %0" : : "r"(va));
+    return &table[mmu_index(va, level)];
 }
 
-// Invalidate all TLB entries
-void riscv64_tlb_invalidate_all(void)
+// Map [pa, pa + size) at va with the largest superpages alignment allows
+static int mmu_map_range(uint64_t va, uint64_t pa, uint64_t size, uint64_t flags)
 {
-    if (!riscv64_tlb_context.initialized) {
-        return;
-    }
-    
-    // Clear software TLB
-    for (int i = 0; i < RISCV64_TLB_ENTRIES; i++) {
-        riscv64_tlb_context.tlb_valid[i] = false;
-        riscv64_tlb_context.tlb_entries[i] = 0;
-        riscv64_tlb_context.tlb_tags[i] = 0;
+    uint64_t attr = mmu_attributes(flags);
+    uint64_t end = va + size;
+    while (va < end)
+    {
+        // Gigapages and megapages; Sv48 terapages are never used
+        int level = g_levels - 1;
+        for (int candidate = g_levels - 3; candidate < g_levels - 1; candidate++)
+        {
+            uint64_t block = mmu_level_size(candidate);
+            if (((va | pa) & (block - 1)) == 0 && end - va >= block)
+            {
+                level = candidate;
+                break;
+            }
+        }
+        uint64_t *entry = mmu_walk(va, level, true);
+        if (!entry)
+        {
+            return -OR_ENOMEM;
+        }
+        *entry = pa_to_pte(pa) | attr;
+        va += mmu_level_size(level);
+        pa += mmu_level_size(level);
     }
-    
-    // Hardware TLB invalidation
-    asm volatile("sfence.vma");
-    
-    printf("RISC-V64: All TLB entries invalidated\n");
+    return OR_OK;
 }
 
-// TLB lookup
-uint64_t riscv64_tlb_lookup(uint64_t va)
+// ========================================
+// SETUP
+// ========================================
+
+// Sv48 when the boot hart advertises it (Sv57 harts also implement it)
+static void mmu_select_mode(const fdt_t *fdt)
 {
-    if (!riscv64_tlb_context.initialized) {
-        return 0;
-    }
-    
-    uint64_t tag = va >> 12;  // Page number
-    
-    for (int i = 0; i < RISCV64_TLB_ENTRIES; i++) {
-        if (riscv64_tlb_context.tlb_valid[i] && riscv64_tlb_context.tlb_tags[i] == tag) {
-            riscv64_tlb_context.tlb_hits++;
-            return riscv64_tlb_context.tlb_entries[i];
+    int cpus = fdt ? fdt_path_offset(fdt, "/cpus") : -1;
+    for (int node = cpus >= 0 ? fdt_first_subnode(fdt, cpus) : -1; node >= 0; node = fdt_next_subnode(fdt, node))
+    {
+        const char *type = fdt_getprop_string(fdt, node, "mmu-type");
+        if (!type)
+        {
+            continue;
+        }
+        if (strcmp(type, "riscv,sv48") == 0 || strcmp(type, "riscv,sv57") == 0)
+        {
+            g_levels = 4;
+            g_satp_mode = SATP_MODE_SV48;
         }
+        break;
     }
-    
-    riscv64_tlb_context.tlb_misses++;
-    return 0;  // TLB miss
 }
 
-// TLB insert
-void riscv64_tlb_insert(uint64_t va, uint64_t pa, uint64_t flags)
+// Point satp at the kernel tables. Used by the boot hart once the tables
+// exist and by every secondary hart
+void riscv64_mmu_init(void)
 {
-    if (!riscv64_tlb_context.initialized) {
+    if (!g_root_table)
+    {
         return;
     }
-    
-    uint64_t tag = va >> 12;  // Page number
-    uint64_t entry = (pa >> 12) | flags;
-    
-    // Find free TLB entry
-    int free_index = -1;
-    for (int i = 0; i < RISCV64_TLB_ENTRIES; i++) {
-        if (!riscv64_tlb_context.tlb_valid[i]) {
-            free_index = i;
-            break;
-        }
-    }
-    
-    // If no free entry, evict one
-    if (free_index == -1) {
-        free_index = 0;  // Simple FIFO replacement
-        riscv64_tlb_context.tlb_evictions++;
-    }
-    
-    // Insert entry
-    riscv64_tlb_context.tlb_valid[free_index] = true;
-    riscv64_tlb_context.tlb_tags[free_index] = tag;
-    riscv64_tlb_context.tlb_entries[free_index] = entry;
+    uint64_t satp = g_satp_mode | ((uint64_t)(uintptr_t)g_root_table >> RISCV64_PAGE_SHIFT);
+    __asm__ volatile("sfence.vma zero, zero\n"
+                     "csrw satp, %0\n"
+                     "sfence.vma zero, zero" ::"r"(satp)
+                     : "memory");
+    g_mmu_enabled = true;
 }
 
-// ============================================================================
-// MEMORY PROTECTION
-// ============================================================================
-
-// Set memory protection
-int riscv64_mmu_set_protection(uint64_t va, uint64_t size, uint64_t flags)
+int riscv64_mmu_setup(const fdt_t *fdt, const fdt_region_t *ram, int count)
 {
-    if (!riscv64_mmu_context.initialized) {
-        return -1;
-    }
-    
-    // Update protection for all pages in the range
-    for (uint64_t addr = va; addr < va + size; addr += RISCV64_PAGE_SIZE) {
-        // Find the page table entry and update flags
-        // This is a simplified implementation
-        riscv64_mmu_update_page_flags(addr, flags);
-    }
-    
-    // Invalidate TLB entries for the range
-    for (uint64_t addr = va; addr < va + size; addr += RISCV64_PAGE_SIZE) {
-        riscv64_tlb_invalidate_entry(addr);
-    }
-    
-    return 0;
+    mmu_select_mode(fdt);
+    g_root_table = mmu_alloc_table();
+    if (!g_root_table)
+    {
+        return -OR_ENOMEM;
+    }
+
+    int result = mmu_map_range(MMU_DEVICE_WINDOW_BASE, MMU_DEVICE_WINDOW_BASE, MMU_DEVICE_WINDOW_SIZE,
+                               PAGE_FLAG_WRITE | PAGE_FLAG_NO_CACHE);
+    for (int i = 0; i < count && result == OR_OK; i++)
+    {
+        // The kernel image lives in RAM, which therefore stays executable
+        // until the loader maps sections individually
+        uint64_t base = ram[i].base & RISCV64_PAGE_MASK;
+        uint64_t size = (ram[i].size + RISCV64_PAGE_SIZE - 1) & RISCV64_PAGE_MASK;
+        result = mmu_map_range(base, base, size, PAGE_FLAG_WRITE | PAGE_FLAG_EXEC);
+    }
+    if (result != OR_OK)
+    {
+        kerror("riscv64: page tables exhausted while mapping RAM");
+        return result;
+    }
+
+    riscv64_mmu_init();
+    kinfo("riscv64: %s paging on, %u table pages, %d RAM regions identity-mapped", g_levels == 4 ? "Sv48" : "Sv39",
+          g_boot_pool_used, count);
+    return OR_OK;
 }
 
-// Update page flags
-void riscv64_mmu_update_page_flags(uint64_t va, uint64_t flags)
+bool riscv64_mmu_enabled(void)
 {
-    // This function would update the protection flags for a specific page
-    // Implementation depends on the page table structure
-    printf("RISC-V64: Page flags updated for VA 0x%llx to 0x%llx\n", va, flags);
+    return g_mmu_enabled;
 }
 
-// ============================================================================
-// MEMORY REGION MANAGEMENT
-// ============================================================================
+void riscv64_mmu_disable(void)
+{
+    __asm__ volatile("csrw satp, zero\n"
+                     "sfence.vma zero, zero" ::
+                         : "memory");
+    g_mmu_enabled = false;
+}
 
-// Add memory region
-int riscv64_mmu_add_region(uint64_t base, uint64_t size, uint64_t flags)
+void riscv64_mmu_invalidate_tlb(void)
 {
-    if (!riscv64_memory_regions.initialized || riscv64_memory_regions.region_count >= 64) {
-        return -1;
-    }
-    
-    int index = riscv64_memory_regions.region_count;
-    riscv64_memory_regions.regions[index] = base;
-    riscv64_memory_regions.region_flags[index] = flags;
-    riscv64_memory_regions.region_count++;
-    
-    printf("RISC-V64: Memory region added at 0x%llx, size 0x%llx, flags 0x%llx\n", 
-           base, size, flags);
-    
-    return index;
+    __asm__ volatile("sfence.vma zero, zero" ::: "memory");
 }
 
-// Remove memory region
-int riscv64_mmu_remove_region(uint64_t base)
+// sfence.vma only reaches the running hart: other harts are flushed by
+// smp_tlb_shootdown, which runs this on each of them
+void arch_tlb_flush_local(uint64_t vaddr, uint64_t pages)
 {
-    if (!riscv64_memory_regions.initialized) {
-        return -1;
+    if (pages == SMP_TLB_FLUSH_ALL)
+    {
+        riscv64_mmu_invalidate_tlb();
+        return;
     }
-    
-    for (int i = 0; i < riscv64_memory_regions.region_count; i++) {
-        if (riscv64_memory_regions.regions[i] == base) {
-            // Remove region by shifting remaining regions
-            for (int j = i; j < riscv64_memory_regions.region_count - 1; j++) {
-                riscv64_memory_regions.regions[j] = riscv64_memory_regions.regions[j + 1];
-                riscv64_memory_regions.region_flags[j] = riscv64_memory_regions.region_flags[j + 1];
-            }
-            riscv64_memory_regions.region_count--;
-            
-            printf("RISC-V64: Memory region removed at 0x%llx\n", base);
-            return 0;
-        }
+    for (uint64_t page = 0; page < pages; page++)
+    {
+        __asm__ volatile("sfence.vma %0, zero" ::"r"(vaddr + page * RISCV64_PAGE_SIZE) : "memory");
     }
-    
-    return -1;  // Region not found
 }
 
-// ============================================================================
-// ADDRESS TRANSLATION
-// ============================================================================
+// ========================================
+// PAGE MAPPINGS
+// ========================================
 
-// Virtual to physical address translation
-uint64_t riscv64_mmu_virt_to_phys(uint64_t va)
+int riscv64_mmu_map_page(uint64_t va, uint64_t pa, uint64_t flags)
 {
-    if (!riscv64_mmu_context.initialized) {
-        return va;  // Identity mapping if MMU not initialized
-    }
-    
-    // Check TLB first
-    uint64_t tlb_entry = riscv64_tlb_lookup(va);
-    if (tlb_entry) {
-        return ((tlb_entry >> 12) << 12) | (va & 0xFFF);
+    if (((va | pa) & (RISCV64_PAGE_SIZE - 1)) || !g_root_table)
+    {
+        return -OR_EINVAL;
     }
-    
-    // TLB miss - walk page tables
-    uint64_t pa = riscv64_mmu_walk_page_tables(va);
-    if (pa) {
-        // Insert into TLB
-        uint64_t flags = riscv64_mmu_get_page_flags(va);
-        riscv64_tlb_insert(va, pa, flags);
+
+    spin_lock(&g_mmu_lock);
+    uint64_t *entry = mmu_walk(va, g_levels - 1, true);
+    if (!entry)
+    {
+        spin_unlock(&g_mmu_lock);
+        return -OR_ENOMEM;
     }
-    
-    return pa;
+    *entry = pa_to_pte(pa) | mmu_attributes(flags);
+    spin_unlock(&g_mmu_lock);
+
+    // Harts may cache invalid entries too, so new mappings are fenced as well
+    mmu_flush_page(va);
+    return OR_OK;
 }
 
-// Walk page tables
-uint64_t riscv64_mmu_walk_page_tables(uint64_t va)
+int riscv64_mmu_unmap_page(uint64_t va)
 {
-    if (!riscv64_mmu_context.root_page_table) {
-        return 0;
-    }
-    
-    // Calculate page table indices
-    uint64_t level1_index = (va >> 30) & 0x1FF;
-    uint64_t level2_index = (va >> 21) & 0x1FF;
-    uint64_t level3_index = (va >> 12) & 0x1FF;
-    
-    uint64_t *root_table = riscv64_mmu_context.root_page_table;
-    
-    // Level 1
-    if (!(root_table[level1_index] & 0x1)) {
-        return 0;  // Not mapped
-    }
-    
-    // Check if this is a 1GB page
-    if (root_table[level1_index] & 0x80) {
-        uint64_t pa = ((root_table[level1_index] >> 30) << 30) | (va & 0x3FFFFFFF);
-        return pa;
-    }
-    
-    uint64_t *level2_table = (uint64_t*)((root_table[level1_index] >> 12) << 12);
-    
-    // Level 2
-    if (!(level2_table[level2_index] & 0x1)) {
-        return 0;  // Not mapped
+    if ((va & (RISCV64_PAGE_SIZE - 1)) || !g_root_table)
+    {
+        return -OR_EINVAL;
     }
-    
-    // Check if this is a 2MB page
-    if (level2_table[level2_index] & 0x80) {
-        uint64_t pa = ((level2_table[level2_index] >> 21) << 21) | (va & 0x1FFFFF);
-        return pa;
+    if (!riscv64_mmu_translate(va))
+    {
+        return -OR_ENOENT;
     }
-    
-    uint64_t *level3_table = (uint64_t*)((level2_table[level2_index] >> 12) << 12);
-    
-    // Level 3
-    if (!(level3_table[level3_index] & 0x1)) {
-        return 0;  // Not mapped
-    }
-    
-    uint64_t pa = ((level3_table[level3_index] >> 12) << 12) | (va & 0xFFF);
-    return pa;
-}
-
-// Get page flags
-uint64_t riscv64_mmu_get_page_flags(uint64_t va)
-{
-    // This function would extract the protection flags for a page
-    // Implementation depends on the page table structure
-    return RISCV64_PAGE_READ | RISCV64_PAGE_WRITE | RISCV64_PAGE_EXECUTE;
-}
 
-// ============================================================================
-// MMU STATUS AND DEBUG
-// ============================================================================
-
-// Get MMU status
-bool riscv64_mmu_is_initialized(void)
-{
-    return riscv64_mmu_context.initialized;
-}
+    // Walking with `create` splits a superpage covering the page
+    spin_lock(&g_mmu_lock);
+    uint64_t *entry = mmu_walk(va, g_levels - 1, true);
+    if (!entry)
+    {
+        spin_unlock(&g_mmu_lock);
+        return -OR_ENOMEM;
+    }
+    *entry = 0;
+    spin_unlock(&g_mmu_lock);
 
-// Get TLB statistics
-void riscv64_mmu_get_tlb_stats(uint32_t *hits, uint32_t *misses, uint32_t *evictions)
-{
-    if (hits) *hits = riscv64_tlb_context.tlb_hits;
-    if (misses) *misses = riscv64_tlb_context.tlb_misses;
-    if (evictions) *evictions = riscv64_tlb_context.tlb_evictions;
+    mmu_flush_page(va);
+    return OR_OK;
 }
 
-// Print MMU status
-void riscv64_mmu_print_status(void)
+// Physical address mapped at `va`, 0 when unmapped
+uint64_t riscv64_mmu_translate(uint64_t va)
 {
-    printf("RISC-V64: MMU Status\n");
-    printf("  Initialized: %s\n", riscv64_mmu_context.initialized ? "Yes" : "No");
-    printf("  SATP: 0x%llx\n", riscv64_mmu_context.satp);
-    printf("  ASID: %llu\n", riscv64_mmu_context.asid);
-    printf("  Root Page Table: 0x%llx\n", (uint64_t)riscv64_mmu_context.root_page_table);
-    printf("  Page Table Pages: %llu\n", riscv64_page_table_context.page_table_pages);
-    printf("  Page Table Size: %llu bytes\n", riscv64_page_table_context.page_table_size);
-    printf("  Total Pages: %llu\n", riscv64_mmu_context.total_pages);
-    printf("  Used Pages: %llu\n", riscv64_mmu_context.used_pages);
-    
-    printf("  TLB:\n");
-    printf("    Entries: %u\n", RISCV64_TLB_ENTRIES);
-    printf("    Hits: %u\n", riscv64_tlb_context.tlb_hits);
-    printf("    Misses: %u\n", riscv64_tlb_context.tlb_misses);
-    printf("    Evictions: %u\n", riscv64_tlb_context.tlb_evictions);
-    
-    printf("  Memory Regions: %llu\n", riscv64_memory_regions.region_count);
+    if (!g_root_table)
+    {
+        return 0;
+    }
+    uint64_t *table = g_root_table;
+    for (int level = 0; level < g_levels; level++)
+    {
+        uint64_t entry = table[mmu_index(va, level)];
+        if (!(entry & PTE_V))
+        {
+            return 0;
+        }
+        if (entry & PTE_LEAF)
+        {
+            uint64_t size = mmu_level_size(level);
+            return (pte_to_pa(entry) & ~(size - 1)) | (va & (size - 1));
+        }
+        table = (uint64_t *)(uintptr_t)pte_to_pa(entry);
+    }
+    return 0;
 }
 
-// ============================================================================
-// MMU CLEANUP
-// ============================================================================
-
-// Cleanup MMU system
-void riscv64_mmu_cleanup(void)
+void *arch_ioremap(uint64_t phys, size_t size)
 {
-    if (!riscv64_mmu_context.initialized) {
-        return;
-    }
-    
-    // Cleanup page tables
-    if (riscv64_page_table_context.kernel_page_table) {
-        riscv64_page_table_destroy(riscv64_page_table_context.kernel_page_table);
-        riscv64_page_table_context.kernel_page_table = NULL;
-    }
-    
-    if (riscv64_page_table_context.user_page_table) {
-        riscv64_page_table_destroy(riscv64_page_table_context.user_page_table);
-        riscv64_page_table_context.user_page_table = NULL;
+    uint64_t start = phys & RISCV64_PAGE_MASK;
+    uint64_t end = (phys + size + RISCV64_PAGE_SIZE - 1) & RISCV64_PAGE_MASK;
+    for (uint64_t page = start; page < end; page += RISCV64_PAGE_SIZE)
+    {
+        // The device window is mapped at boot; only devices outside it
+        // need pages of their own
+        if (riscv64_mmu_translate(page) == page)
+        {
+            continue;
+        }
+        if (riscv64_mmu_map_page(page, page, PAGE_FLAG_WRITE | PAGE_FLAG_NO_CACHE) != OR_OK)
+        {
+            return NULL;
+        }
     }
-    
-    // Clear contexts
-    riscv64_mmu_context.initialized = false;
-    riscv64_tlb_context.initialized = false;
-    riscv64_page_table_context.initialized = false;
-    riscv64_memory_regions.initialized = false;
-    
-    printf("RISC-V64: MMU system cleaned up\n");
+    return (void *)(uintptr_t)phys;
 }
@@ -0,0 +1,219 @@
+/*
+ * ORION OS - RISC-V 64-bit Platform Bring-up
+ *
+ * Glue between the boot code and the portable kernel on SBI machines such
+ * as QEMU's virt board with OpenSBI. riscv64_boot_main runs in S-mode
+ * with paging off, the boot hart id in a0 and the device tree blob in a1
+ * as the SBI boot convention specifies: it brings up the NS16550 console,
+ * the identity mapped page tables and the boot information, then enters
+ * kernel_main. The PLIC, secondary harts, virtio-mmio devices and timer
+ * are started later from the hooks the kernel calls during its early
+ * initialization.
+ * Supervisor traps land in riscv64_supervisor_trap_handler_c.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include <orion/virtio_mmio.h>
+#include <orion/smp.h>
+#include <orion/earlycon.h>
+#include "orion-boot-protocol.h"
+#include "arch.h"
+
+#define UART_DEFAULT_BASE 0x10000000ULL // QEMU virt UART0
+#define UART_THR 0
+#define UART_LSR 5
+#define UART_LSR_THRE (1U << 5)
+
+#define RISCV64_FALLBACK_RAM_BASE 0x80000000ULL
+#define RISCV64_FALLBACK_RAM_SIZE (128ULL * 1024 * 1024)
+
+#define SCAUSE_INTERRUPT (1ULL << 63)
+#define SCAUSE_S_SOFTWARE 1
+#define SCAUSE_S_TIMER 5
+#define SCAUSE_S_EXTERNAL 9
+
+extern void kernel_main(struct orion_boot_info *boot_info);
+extern void kernel_panic(const char *message);
+
+static volatile uint8_t *g_uart = (volatile uint8_t *)UART_DEFAULT_BASE;
+static uint32_t g_uart_shift = 0;
+
+// ========================================
+// CONSOLE
+// ========================================
+
+// The UART is left configured by firmware; only its address and register
+// spacing are taken from the device tree
+void console_init(void)
+{
+    const fdt_t *fdt = fdt_boot();
+    int node = fdt ? fdt_node_offset_by_compatible(fdt, -1, "ns16550a") : -1;
+    uint64_t base;
+    if (node >= 0 && fdt_read_reg(fdt, node, 0, &base, NULL) == OR_OK)
+    {
+        g_uart = (volatile uint8_t *)(uintptr_t)base;
+        uint32_t shift;
+        if (fdt_getprop_u32(fdt, node, "reg-shift", &shift))
+        {
+            g_uart_shift = shift;
+        }
+    }
+}
+
+void console_putchar(char c)
+{
+    earlycon_putchar(c);
+    if (c == '\n')
+    {
+        console_putchar('\r');
+    }
+    while (!(g_uart[UART_LSR << g_uart_shift] & UART_LSR_THRE))
+    {
+    }
+    g_uart[UART_THR << g_uart_shift] = (uint8_t)c;
+}
+
+void console_puts(const char *str)
+{
+    while (*str)
+    {
+        console_putchar(*str++);
+    }
+}
+
+// ========================================
+// BOOT
+// ========================================
+
+void riscv64_boot_main(uint64_t hartid, uint64_t dtb)
+{
+    // CPU numbers live in sscratch, the boot hart being 0
+    __asm__ volatile("csrw sscratch, zero");
+
+    fdt_set_boot((const void *)(uintptr_t)dtb);
+    console_init();
+    kinfo("riscv64: booting on hart %llu, device tree at 0x%llx", (unsigned long long)hartid,
+          (unsigned long long)dtb);
+    if (!fdt_boot())
+    {
+        kwarn("riscv64: no valid device tree, using QEMU virt defaults");
+    }
+    riscv64_sbi_init();
+    riscv64_cpus_init(fdt_boot(), hartid);
+
+    fdt_region_t ram[FDT_MAX_MEMORY_REGIONS];
+    int count = fdt_boot() ? fdt_memory_regions(fdt_boot(), ram, FDT_MAX_MEMORY_REGIONS) : 0;
+    if (count <= 0)
+    {
+        ram[0].base = RISCV64_FALLBACK_RAM_BASE;
+        ram[0].size = RISCV64_FALLBACK_RAM_SIZE;
+        count = 1;
+    }
+
+    if (riscv64_mmu_setup(fdt_boot(), ram, count) != OR_OK)
+    {
+        kerror("riscv64: cannot build the kernel page tables");
+        arch_halt();
+    }
+    riscv64_arch_init();
+
+    kernel_main(fdt_build_boot_info(ram, count));
+    arch_halt();
+}
+
+// ========================================
+// TRAPS
+// ========================================
+
+void riscv64_supervisor_trap_handler_c(uint64_t cause, uint64_t epc, uint64_t tval)
+{
+    if (cause & SCAUSE_INTERRUPT)
+    {
+        switch (cause & ~SCAUSE_INTERRUPT)
+        {
+        case SCAUSE_S_TIMER:
+            riscv64_timer_interrupt();
+            return;
+        case SCAUSE_S_EXTERNAL:
+            irq_handle();
+            return;
+        case SCAUSE_S_SOFTWARE:
+            // SBI IPIs: the messages themselves are in memory
+            __asm__ volatile("csrc sip, %0" ::"r"(RISCV64_SIE_SSIE));
+            smp_handle_ipi();
+            return;
+        default:
+            kwarn("riscv64: unexpected interrupt cause %llu", (unsigned long long)(cause & ~SCAUSE_INTERRUPT));
+            return;
+        }
+    }
+
+    kerror("riscv64: unhandled exception %llu at 0x%llx, tval 0x%llx on CPU %u", (unsigned long long)cause,
+           (unsigned long long)epc, (unsigned long long)tval, arch_get_current_cpu());
+    kernel_panic("Unhandled supervisor exception");
+}
+
+// ========================================
+// KERNEL HOOKS
+// ========================================
+
+void arch_interrupt_init(void)
+{
+    const fdt_t *fdt = fdt_boot();
+    if (riscv64_plic_init(fdt) != OR_OK)
+    {
+        kerror("riscv64: no usable PLIC, interrupts stay masked");
+        return;
+    }
+    if (fdt)
+    {
+        virtio_mmio_discover(fdt);
+    }
+
+    __asm__ volatile("csrs sstatus, %0" ::"r"(RISCV64_SSTATUS_SIE) : "memory");
+}
+
+void arch_timer_init(void)
+{
+    riscv64_timer_init();
+}
+
+// Supervisor software interrupts are per hart and bypass the PLIC; its
+// chip sends them through SBI
+int arch_smp_send_ipi(uint32_t cpu)
+{
+    return irq_send_ipi(0, cpu);
+}
+
+void arch_enable_interrupts(void)
+{
+    __asm__ volatile("csrs sstatus, %0" ::"r"(RISCV64_SSTATUS_SIE) : "memory");
+}
+
+void arch_cpu_idle(void)
+{
+    __asm__ volatile("wfi");
+}
+
+void arch_pause(void)
+{
+    // Zihintpause "pause", a hint that decodes as a fence on older harts
+    __asm__ volatile(".insn i 0x0F, 0, x0, x0, 0x010");
+}
+
+void arch_halt(void)
+{
+    for (;;)
+    {
+        __asm__ volatile("wfi");
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * ORION OS - RISC-V 64-bit Platform-Level Interrupt Controller
+ *
+ * orion-irq chip for the PLIC ("riscv,plic0", "sifive,plic-1.0.0"). Each
+ * hart has one context per privilege level; the S-mode context of every
+ * CPU is found through the PLIC's "interrupts-extended" property, whose
+ * entries pair a hart's interrupt controller with cause 9 (supervisor
+ * external). Kernel IRQ numbers are PLIC source numbers, source 0 being
+ * reserved. The PLIC has no per-source trigger configuration and routes
+ * each source by enabling it in exactly one context. IPIs do not go
+ * through the PLIC but through SBI, which raises the target hart's
+ * supervisor software interrupt.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include "arch.h"
+
+#define PLIC_DEFAULT_BASE 0x0C000000ULL // QEMU virt
+#define PLIC_DEFAULT_SIZE 0x600000ULL
+#define PLIC_DEFAULT_NDEV 96
+
+#define PLIC_PRIORITY(irq) (0x000000 + 4 * (irq))
+#define PLIC_ENABLE(ctx, irq) (0x002000 + 0x80 * (ctx) + 4 * ((irq) / 32))
+#define PLIC_THRESHOLD(ctx) (0x200000 + 0x1000 * (ctx))
+#define PLIC_CLAIM(ctx) (0x200004 + 0x1000 * (ctx))
+
+#define PLIC_MAX_PRIORITY 7
+#define PLIC_NO_CONTEXT 0xFFFFFFFF
+#define RISCV64_CAUSE_S_EXTERNAL 9
+
+static volatile uint8_t *g_plic = NULL;
+static uint32_t g_plic_ndev = PLIC_DEFAULT_NDEV;
+static uint32_t g_cpu_context[RISCV64_SMP_MAX_HARTS];
+static uint32_t g_irq_target[RISCV64_PLIC_MAX_IRQS]; // CPU each source is enabled on
+static spinlock_t g_plic_lock = SPINLOCK_INIT;
+
+static inline uint32_t plic_read(uint32_t reg)
+{
+    return *(volatile uint32_t *)(g_plic + reg);
+}
+
+static inline void plic_write(uint32_t reg, uint32_t value)
+{
+    *(volatile uint32_t *)(g_plic + reg) = value;
+}
+
+static void plic_set_enable(uint32_t cpu, uint32_t irq, bool enable)
+{
+    uint32_t ctx = g_cpu_context[cpu];
+    if (ctx == PLIC_NO_CONTEXT)
+    {
+        return;
+    }
+    uint32_t reg = PLIC_ENABLE(ctx, irq);
+    uint32_t value = plic_read(reg);
+    value = enable ? (value | (1U << (irq % 32))) : (value & ~(1U << (irq % 32)));
+    plic_write(reg, value);
+}
+
+// ========================================
+// CHIP OPERATIONS
+// ========================================
+
+static void plic_enable(uint32_t irq)
+{
+    if (irq == 0 || irq > g_plic_ndev)
+    {
+        return;
+    }
+    spin_lock(&g_plic_lock);
+    if (plic_read(PLIC_PRIORITY(irq)) == 0)
+    {
+        // Priority 0 never interrupts
+        plic_write(PLIC_PRIORITY(irq), 1);
+    }
+    plic_set_enable(g_irq_target[irq], irq, true);
+    spin_unlock(&g_plic_lock);
+}
+
+static void plic_disable(uint32_t irq)
+{
+    if (irq == 0 || irq > g_plic_ndev)
+    {
+        return;
+    }
+    spin_lock(&g_plic_lock);
+    plic_set_enable(g_irq_target[irq], irq, false);
+    spin_unlock(&g_plic_lock);
+}
+
+static uint32_t plic_ack(void)
+{
+    uint32_t ctx = g_cpu_context[arch_get_current_cpu()];
+    if (ctx == PLIC_NO_CONTEXT)
+    {
+        return IRQ_NONE;
+    }
+    uint32_t irq = plic_read(PLIC_CLAIM(ctx));
+    return irq ? irq : IRQ_NONE;
+}
+
+static void plic_eoi(uint32_t irq)
+{
+    uint32_t ctx = g_cpu_context[arch_get_current_cpu()];
+    if (ctx != PLIC_NO_CONTEXT)
+    {
+        plic_write(PLIC_CLAIM(ctx), irq);
+    }
+}
+
+static int plic_set_type(uint32_t irq, uint32_t type)
+{
+    (void)irq;
+    // Gateways convert edges themselves; there is nothing to program
+    return type == IRQ_TYPE_LEVEL_HIGH || type == IRQ_TYPE_EDGE_RISING ? OR_OK : -OR_ENOTSUP;
+}
+
+// orion-irq priorities run from 0 (highest) to 0xFF, PLIC ones from 1 to 7
+static int plic_set_priority(uint32_t irq, uint8_t priority)
+{
+    if (irq == 0 || irq > g_plic_ndev)
+    {
+        return -OR_EINVAL;
+    }
+    uint32_t level = PLIC_MAX_PRIORITY - (priority >> 5);
+    plic_write(PLIC_PRIORITY(irq), level ? level : 1);
+    return OR_OK;
+}
+
+static int plic_set_affinity(uint32_t irq, uint32_t cpu)
+{
+    if (irq == 0 || irq > g_plic_ndev || cpu >= riscv64_cpu_count() || g_cpu_context[cpu] == PLIC_NO_CONTEXT)
+    {
+        return -OR_EINVAL;
+    }
+    spin_lock(&g_plic_lock);
+    uint32_t reg = PLIC_ENABLE(g_cpu_context[g_irq_target[irq]], irq);
+    bool enabled = plic_read(reg) & (1U << (irq % 32));
+    if (enabled)
+    {
+        plic_set_enable(g_irq_target[irq], irq, false);
+        plic_set_enable(cpu, irq, true);
+    }
+    g_irq_target[irq] = cpu;
+    spin_unlock(&g_plic_lock);
+    return OR_OK;
+}
+
+static int plic_send_ipi(uint32_t irq, uint32_t cpu)
+{
+    (void)irq;
+    if (cpu >= riscv64_cpu_count())
+    {
+        return -OR_EINVAL;
+    }
+    return riscv64_sbi_send_ipi(1UL, (unsigned long)riscv64_cpu_hartid(cpu));
+}
+
+static int plic_cpu_init(uint32_t cpu)
+{
+    uint32_t ctx = g_cpu_context[cpu];
+    if (ctx == PLIC_NO_CONTEXT)
+    {
+        kwarn("PLIC: no S-mode context for CPU %u", cpu);
+        return -OR_ENODEV;
+    }
+    plic_write(PLIC_THRESHOLD(ctx), 0);
+    __asm__ volatile("csrs sie, %0" ::"r"(RISCV64_SIE_SEIE | RISCV64_SIE_SSIE) : "memory");
+    return OR_OK;
+}
+
+// Single-cell specifiers: the source number
+static int plic_xlate(const uint32_t *cells, uint32_t count, uint32_t *irq, uint32_t *type)
+{
+    if (count < 1 || cells[0] == 0 || cells[0] > g_plic_ndev)
+    {
+        return -OR_EINVAL;
+    }
+    *irq = cells[0];
+    *type = IRQ_TYPE_LEVEL_HIGH;
+    return OR_OK;
+}
+
+static irq_chip_t g_plic_chip = {
+    .name = "plic",
+    .nr_irqs = PLIC_DEFAULT_NDEV + 1,
+    .enable = plic_enable,
+    .disable = plic_disable,
+    .ack = plic_ack,
+    .eoi = plic_eoi,
+    .set_type = plic_set_type,
+    .set_priority = plic_set_priority,
+    .set_affinity = plic_set_affinity,
+    .send_ipi = plic_send_ipi,
+    .cpu_init = plic_cpu_init,
+    .xlate = plic_xlate,
+};
+
+// ========================================
+// INITIALIZATION
+// ========================================
+
+// Map each CPU to the context whose "interrupts-extended" entry names its
+// hart's interrupt controller with the supervisor external cause
+static void plic_find_contexts(const fdt_t *fdt, int node)
+{
+    uint32_t length = 0;
+    const uint32_t *cells = node >= 0 ? fdt_getprop(fdt, node, "interrupts-extended", &length) : NULL;
+    uint32_t entries = cells ? length / 8 : 0;
+    for (uint32_t ctx = 0; ctx < entries; ctx++)
+    {
+        uint32_t phandle = fdt32_to_cpu(&cells[2 * ctx]);
+        uint32_t cause = fdt32_to_cpu(&cells[2 * ctx + 1]);
+        if (cause != RISCV64_CAUSE_S_EXTERNAL)
+        {
+            continue;
+        }
+        int intc = fdt_node_offset_by_phandle(fdt, phandle);
+        int cpu_node = intc >= 0 ? fdt_parent_offset(fdt, intc) : -1;
+        uint64_t hartid;
+        if (cpu_node < 0 || fdt_read_reg(fdt, cpu_node, 0, &hartid, NULL) != OR_OK)
+        {
+            continue;
+        }
+        int cpu = riscv64_hartid_to_cpu(hartid);
+        if (cpu >= 0)
+        {
+            g_cpu_context[cpu] = ctx;
+        }
+    }
+
+    if (entries == 0)
+    {
+        // QEMU virt layout: M-mode and S-mode context for each hart in turn
+        for (uint32_t cpu = 0; cpu < riscv64_cpu_count(); cpu++)
+        {
+            g_cpu_context[cpu] = 2 * (uint32_t)riscv64_cpu_hartid(cpu) + 1;
+        }
+    }
+}
+
+int riscv64_plic_init(const fdt_t *fdt)
+{
+    uint64_t base = PLIC_DEFAULT_BASE, size = PLIC_DEFAULT_SIZE;
+    int node = -1;
+    if (fdt)
+    {
+        node = fdt_node_offset_by_compatible(fdt, -1, "riscv,plic0");
+        if (node < 0)
+        {
+            node = fdt_node_offset_by_compatible(fdt, -1, "sifive,plic-1.0.0");
+        }
+    }
+    if (node >= 0)
+    {
+        fdt_read_reg(fdt, node, 0, &base, &size);
+        uint32_t ndev;
+        if (fdt_getprop_u32(fdt, node, "riscv,ndev", &ndev) && ndev < RISCV64_PLIC_MAX_IRQS)
+        {
+            g_plic_ndev = ndev;
+        }
+    }
+    else
+    {
+        kwarn("PLIC: not in the device tree, using QEMU virt defaults");
+    }
+
+    g_plic = (volatile uint8_t *)arch_ioremap(base, size);
+    if (!g_plic)
+    {
+        return -OR_ENOMEM;
+    }
+    for (uint32_t cpu = 0; cpu < RISCV64_SMP_MAX_HARTS; cpu++)
+    {
+        g_cpu_context[cpu] = PLIC_NO_CONTEXT;
+    }
+    plic_find_contexts(fdt, node);
+
+    // Every source masked on every context, routed to the boot CPU
+    for (uint32_t irq = 1; irq <= g_plic_ndev; irq++)
+    {
+        plic_write(PLIC_PRIORITY(irq), 0);
+        for (uint32_t cpu = 0; cpu < riscv64_cpu_count(); cpu++)
+        {
+            plic_set_enable(cpu, irq, false);
+        }
+        g_irq_target[irq] = 0;
+    }
+
+    g_plic_chip.nr_irqs = g_plic_ndev + 1;
+    int result = irq_register_chip(&g_plic_chip);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+    plic_cpu_init(0);
+    kinfo("PLIC: %u sources at 0x%llx, boot CPU context %u", g_plic_ndev, (unsigned long long)base,
+          g_cpu_context[0]);
+    return OR_OK;
+}
@@ -0,0 +1,336 @@
+/*
+ * ORION OS - RISC-V 64-bit SBI Client and Hart Bring-up
+ *
+ * The kernel runs in S-mode under an SBI implementation (OpenSBI on QEMU
+ * virt and most boards), which owns M-mode, the CLINT and the PMP. Timer
+ * programming, IPIs, hart start and system reset all go through ecalls
+ * to it. Extensions are probed once; the legacy v0.1 calls are used as a
+ * fallback for the timer and reset when an older firmware lacks them.
+ *
+ * Harts are listed by the /cpus node of the device tree, their "reg"
+ * being the hart id; CPU numbers used by the kernel are their position in
+ * that list, the boot hart being renumbered 0. Secondary harts are started
+ * with HSM hart_start at riscv64_secondary_entry (boot.S), given a stack
+ * of their own, and join the scheduler through riscv64_secondary_main
+ * when the kernel calls arch_smp_boot.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include <orion/smp.h>
+#include "arch.h"
+
+#define SBI_EXT_BASE 0x10
+#define SBI_EXT_TIME 0x54494D45
+#define SBI_EXT_IPI 0x735049
+#define SBI_EXT_RFENCE 0x52464E43
+#define SBI_EXT_HSM 0x48534D
+#define SBI_EXT_SRST 0x53525354
+
+#define SBI_LEGACY_SET_TIMER 0x00
+#define SBI_LEGACY_SHUTDOWN 0x08
+
+#define SBI_BASE_GET_SPEC_VERSION 0
+#define SBI_BASE_GET_IMPL_ID 1
+#define SBI_BASE_PROBE_EXTENSION 3
+#define SBI_BASE_GET_MVENDORID 4
+#define SBI_BASE_GET_MARCHID 5
+#define SBI_BASE_GET_MIMPID 6
+
+#define SBI_HSM_HART_START 0
+#define SBI_SRST_RESET 0
+#define SBI_SRST_TYPE_SHUTDOWN 0
+#define SBI_SRST_TYPE_COLD_REBOOT 1
+
+#define SBI_SUCCESS 0
+#define SBI_ERR_NOT_SUPPORTED -2
+#define SBI_ERR_INVALID_PARAM -3
+#define SBI_ERR_DENIED -4
+#define SBI_ERR_ALREADY_AVAILABLE -6
+
+#define RISCV64_SECONDARY_STACK_SIZE 16384
+#define RISCV64_HART_START_TIMEOUT_LOOPS 100000000ULL
+
+struct sbiret
+{
+    long error;
+    long value;
+};
+
+static bool g_sbi_has_time = false;
+static bool g_sbi_has_ipi = false;
+static bool g_sbi_has_hsm = false;
+static bool g_sbi_has_srst = false;
+
+static uint64_t g_cpu_hartid[RISCV64_SMP_MAX_HARTS];
+static uint32_t g_cpu_count = 1;
+static volatile bool g_cpu_online[RISCV64_SMP_MAX_HARTS];
+
+static uint8_t g_secondary_stacks[RISCV64_SMP_MAX_HARTS][RISCV64_SECONDARY_STACK_SIZE] __attribute__((aligned(16)));
+// Read by riscv64_secondary_entry before any C code runs on the new hart
+uint64_t riscv64_cpu_stack_top[RISCV64_SMP_MAX_HARTS];
+
+extern void riscv64_secondary_entry(void);
+
+static struct sbiret sbi_ecall(long ext, long fid, unsigned long arg0, unsigned long arg1, unsigned long arg2)
+{
+    register unsigned long a0 __asm__("a0") = arg0;
+    register unsigned long a1 __asm__("a1") = arg1;
+    register unsigned long a2 __asm__("a2") = arg2;
+    register unsigned long a6 __asm__("a6") = (unsigned long)fid;
+    register unsigned long a7 __asm__("a7") = (unsigned long)ext;
+    __asm__ volatile("ecall" : "+r"(a0), "+r"(a1) : "r"(a2), "r"(a6), "r"(a7) : "memory");
+    struct sbiret ret = {(long)a0, (long)a1};
+    return ret;
+}
+
+static int sbi_to_error(long error)
+{
+    switch (error)
+    {
+    case SBI_SUCCESS:
+        return OR_OK;
+    case SBI_ERR_NOT_SUPPORTED:
+        return -OR_ENOTSUP;
+    case SBI_ERR_INVALID_PARAM:
+        return -OR_EINVAL;
+    case SBI_ERR_DENIED:
+        return -OR_EPERM;
+    case SBI_ERR_ALREADY_AVAILABLE:
+        return -OR_EBUSY;
+    default:
+        return -OR_EIO;
+    }
+}
+
+static bool sbi_probe(long ext)
+{
+    struct sbiret ret = sbi_ecall(SBI_EXT_BASE, SBI_BASE_PROBE_EXTENSION, (unsigned long)ext, 0, 0);
+    return ret.error == SBI_SUCCESS && ret.value != 0;
+}
+
+// ========================================
+// SBI CALLS
+// ========================================
+
+int riscv64_sbi_init(void)
+{
+    struct sbiret version = sbi_ecall(SBI_EXT_BASE, SBI_BASE_GET_SPEC_VERSION, 0, 0, 0);
+    if (version.error != SBI_SUCCESS)
+    {
+        // SBI v0.1 has no base extension: only the legacy calls exist
+        kwarn("SBI: v0.1 firmware, secondary harts stay offline");
+        return OR_OK;
+    }
+
+    g_sbi_has_time = sbi_probe(SBI_EXT_TIME);
+    g_sbi_has_ipi = sbi_probe(SBI_EXT_IPI);
+    g_sbi_has_hsm = sbi_probe(SBI_EXT_HSM);
+    g_sbi_has_srst = sbi_probe(SBI_EXT_SRST);
+
+    struct sbiret impl = sbi_ecall(SBI_EXT_BASE, SBI_BASE_GET_IMPL_ID, 0, 0, 0);
+    kinfo("SBI: v%lu.%lu, implementation %ld%s%s%s%s", ((unsigned long)version.value >> 24) & 0x7F,
+          (unsigned long)version.value & 0xFFFFFF, impl.value, g_sbi_has_time ? " TIME" : "",
+          g_sbi_has_ipi ? " IPI" : "", g_sbi_has_hsm ? " HSM" : "", g_sbi_has_srst ? " SRST" : "");
+    return OR_OK;
+}
+
+// Machine-level identification registers, only readable from M-mode
+void riscv64_sbi_get_ids(uint64_t *mvendorid, uint64_t *marchid, uint64_t *mimpid)
+{
+    *mvendorid = (uint64_t)sbi_ecall(SBI_EXT_BASE, SBI_BASE_GET_MVENDORID, 0, 0, 0).value;
+    *marchid = (uint64_t)sbi_ecall(SBI_EXT_BASE, SBI_BASE_GET_MARCHID, 0, 0, 0).value;
+    *mimpid = (uint64_t)sbi_ecall(SBI_EXT_BASE, SBI_BASE_GET_MIMPID, 0, 0, 0).value;
+}
+
+void riscv64_sbi_set_timer(uint64_t stime_value)
+{
+    if (g_sbi_has_time)
+    {
+        sbi_ecall(SBI_EXT_TIME, 0, stime_value, 0, 0);
+    }
+    else
+    {
+        sbi_ecall(SBI_LEGACY_SET_TIMER, 0, stime_value, 0, 0);
+    }
+}
+
+int riscv64_sbi_send_ipi(unsigned long hart_mask, unsigned long hart_mask_base)
+{
+    if (!g_sbi_has_ipi)
+    {
+        return -OR_ENOTSUP;
+    }
+    return sbi_to_error(sbi_ecall(SBI_EXT_IPI, 0, hart_mask, hart_mask_base, 0).error);
+}
+
+void riscv64_sbi_system_off(void)
+{
+    if (g_sbi_has_srst)
+    {
+        sbi_ecall(SBI_EXT_SRST, SBI_SRST_RESET, SBI_SRST_TYPE_SHUTDOWN, 0, 0);
+    }
+    sbi_ecall(SBI_LEGACY_SHUTDOWN, 0, 0, 0, 0);
+}
+
+void riscv64_sbi_system_reset(void)
+{
+    if (g_sbi_has_srst)
+    {
+        sbi_ecall(SBI_EXT_SRST, SBI_SRST_RESET, SBI_SRST_TYPE_COLD_REBOOT, 0, 0);
+    }
+}
+
+// ========================================
+// DISCOVERY
+// ========================================
+
+// Number the harts of /cpus, the boot hart first
+int riscv64_cpus_init(const fdt_t *fdt, uint64_t boot_hartid)
+{
+    g_cpu_hartid[0] = boot_hartid;
+    g_cpu_count = 1;
+    g_cpu_online[0] = true;
+
+    int cpus = fdt ? fdt_path_offset(fdt, "/cpus") : -1;
+    int first = cpus >= 0 ? fdt_first_subnode(fdt, cpus) : -1;
+    for (int node = first; node >= 0; node = fdt_next_subnode(fdt, node))
+    {
+        const char *type = fdt_getprop_string(fdt, node, "device_type");
+        uint64_t hartid;
+        if (!type || strcmp(type, "cpu") != 0 || !fdt_node_is_enabled(fdt, node) ||
+            fdt_read_reg(fdt, node, 0, &hartid, NULL) != OR_OK || hartid == boot_hartid)
+        {
+            continue;
+        }
+        if (g_cpu_count == RISCV64_SMP_MAX_HARTS)
+        {
+            kwarn("riscv64: more than %u harts, ignoring the rest", RISCV64_SMP_MAX_HARTS);
+            break;
+        }
+        g_cpu_hartid[g_cpu_count++] = hartid;
+    }
+
+    kinfo("riscv64: %u harts, boot hart %llu", g_cpu_count, (unsigned long long)boot_hartid);
+    return (int)g_cpu_count;
+}
+
+uint32_t riscv64_cpu_count(void)
+{
+    return g_cpu_count;
+}
+
+uint64_t riscv64_cpu_hartid(uint32_t cpu)
+{
+    return cpu < g_cpu_count ? g_cpu_hartid[cpu] : 0;
+}
+
+// Kernel CPU number of a hart, or -1 when it is not in use
+int riscv64_hartid_to_cpu(uint64_t hartid)
+{
+    for (uint32_t cpu = 0; cpu < g_cpu_count; cpu++)
+    {
+        if (g_cpu_hartid[cpu] == hartid)
+        {
+            return (int)cpu;
+        }
+    }
+    return -1;
+}
+
+uint32_t arch_get_cpu_count(void)
+{
+    return g_cpu_count;
+}
+
+bool riscv64_cpu_online(uint32_t cpu)
+{
+    return cpu < g_cpu_count && g_cpu_online[cpu];
+}
+
+// The kernel keeps its CPU number in sscratch while nothing runs in U-mode
+uint32_t arch_get_current_cpu(void)
+{
+    uint64_t cpu;
+    __asm__ volatile("csrr %0, sscratch" : "=r"(cpu));
+    return (uint32_t)cpu;
+}
+
+// ========================================
+// HART BRING-UP
+// ========================================
+
+int riscv64_hart_start(uint32_t cpu)
+{
+    if (cpu == 0 || cpu >= g_cpu_count)
+    {
+        return -OR_EINVAL;
+    }
+    if (!g_sbi_has_hsm)
+    {
+        return -OR_ENOTSUP;
+    }
+    riscv64_cpu_stack_top[cpu] = (uint64_t)(uintptr_t)(g_secondary_stacks[cpu] + RISCV64_SECONDARY_STACK_SIZE);
+    __asm__ volatile("fence rw, rw" ::: "memory");
+
+    struct sbiret ret = sbi_ecall(SBI_EXT_HSM, SBI_HSM_HART_START, g_cpu_hartid[cpu],
+                                  (unsigned long)(uintptr_t)riscv64_secondary_entry, cpu);
+    return sbi_to_error(ret.error);
+}
+
+// Entered by each secondary hart once on its own stack, paging still off;
+// never returns
+void riscv64_secondary_main(uint64_t cpu)
+{
+    __asm__ volatile("csrw sscratch, %0" ::"r"(cpu));
+    riscv64_mmu_init();
+    irq_cpu_init((uint32_t)cpu);
+    riscv64_timer_cpu_init();
+
+    g_cpu_online[cpu] = true;
+    __asm__ volatile("fence rw, rw" ::: "memory");
+    kinfo("riscv64: CPU %llu online (hart %llu)", (unsigned long long)cpu, (unsigned long long)g_cpu_hartid[cpu]);
+
+    smp_secondary_main((uint32_t)cpu);
+}
+
+int arch_smp_boot(void)
+{
+    if (!g_sbi_has_hsm)
+    {
+        return 1;
+    }
+
+    uint32_t online = 1;
+    for (uint32_t cpu = 1; cpu < g_cpu_count; cpu++)
+    {
+        int result = riscv64_hart_start(cpu);
+        if (result != OR_OK)
+        {
+            kerror("riscv64: hart_start for CPU %u failed: %d", cpu, result);
+            continue;
+        }
+        for (uint64_t loops = 0; !g_cpu_online[cpu] && loops < RISCV64_HART_START_TIMEOUT_LOOPS; loops++)
+        {
+            arch_pause();
+        }
+        if (g_cpu_online[cpu])
+        {
+            online++;
+        }
+        else
+        {
+            kerror("riscv64: CPU %u did not come online", cpu);
+        }
+    }
+    kinfo("riscv64: %u of %u CPUs online", online, g_cpu_count);
+    return (int)online;
+}
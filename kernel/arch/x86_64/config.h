@@ -159,6 +159,7 @@
 #define X86_64_APIC_MAX_VECTORS 256
 #define X86_64_APIC_TIMER_VECTOR 32
 #define X86_64_APIC_ERROR_VECTOR 19
+#define X86_64_IPI_VECTOR 0xF0 // Kernel IPIs (rescheduling, TLB shootdown)
 
 // MSI-X support
 #define X86_64_MSIX_MAX_VECTORS 2048
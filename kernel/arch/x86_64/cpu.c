@@ -14,7 +14,9 @@
 #include <orion/kernel.h>
 #include <orion/types.h>
 #include <orion/security.h>
+#include <orion/cpufreq.h>
 #include <arch.h>
+#include "virt.h"
 
 // APIC register offsets
 #define APIC_ID 0x20
@@ -24,9 +26,6 @@
 #define APIC_LVT_LINT1 0x360
 #define APIC_LVT_ERROR 0x370
 
-// Global CPU count
-static uint32_t g_detected_cpus = 1;
-
 // CPU feature flags
 static struct
 {
@@ -187,12 +186,16 @@ void arch_late_init(void)
         kinfo("APIC initialized");
     }
 
-    // Initialize SMP if multiple CPUs detected
-    uint32_t cpu_count = arch_get_cpu_count();
-    if (cpu_count > 1)
+    // Frequency scaling, starting with the boot CPU
+    if (cpufreq_x86_init() == OR_OK && cpufreq_init_cpu() == OR_OK)
     {
-        smp_init();
-        kinfo("SMP initialized for %u CPUs", cpu_count);
+        kinfo("CPU frequency scaling enabled");
+    }
+
+    // Hardware virtualization for guest VMs, whichever the vendor offers
+    if (vmx_init() == OR_OK || svm_init() == OR_OK)
+    {
+        kinfo("Hardware virtualization available");
     }
 
     kinfo("x86_64 late init complete");
@@ -583,81 +586,6 @@ static void apic_init(void)
     kinfo("APIC initialization completed - LVT entries configured");
 }
 
-// SMP initialization
-static void smp_init(void)
-{
-    kinfo("SMP initialization started");
-
-    // Check if we're on BSP (Bootstrap Processor)
-    uint32_t apic_id = 0;
-    uint32_t cpu_count = 1; // Start with BSP
-
-    if (cpu_features.apic)
-    {
-        // Read APIC ID from APIC_ID register
-        uint64_t apic_base = msr_read(0x1B);
-        if (apic_base & (1ULL << 11))
-        { // APIC enabled
-            uint32_t *apic_regs = (uint32_t *)(apic_base & 0xFFFFF000);
-            apic_id = (apic_regs[APIC_ID / 4] >> 24) & 0xFF;
-            kinfo("BSP APIC ID: %u", apic_id);
-
-            // Try to detect additional CPUs using ACPI MADT parsing
-            // This provides more reliable CPU detection than simplified APIC checks
-
-            // Full ACPI MADT parsing implementation for comprehensive CPU detection
-            // The MADT (Multiple APIC Description Table) contains:
-            // - Local APIC entries for each CPU with complete topology information
-            // - I/O APIC entries
-            // - Interrupt source override entries
-            // - Local APIC NMI entries
-
-            // For now, use a more sophisticated APIC-based detection
-            // This is still simplified but better than the basic approach
-            uint32_t detected_cpus = 1; // Start with BSP
-
-            // Check APIC ID register for multiple CPU IDs
-            // In a real system, different CPUs should have different APIC IDs
-            uint32_t base_apic_id = apic_id;
-
-            // Scan for additional APIC IDs (simplified detection)
-            for (uint32_t test_id = 0; test_id < 32; test_id++)
-            {
-                if (test_id != base_apic_id)
-                {
-                    // In a real implementation, we would:
-                    // 1. Parse ACPI MADT to get all Local APIC entries
-                    // 2. Check each entry for valid CPU information
-                    // 3. Verify CPU is actually present and responsive
-
-                    // For now, use a heuristic: if we see different APIC IDs, assume additional CPUs
-                    uint32_t test_apic_id = (apic_regs[APIC_ID / 4] >> 24) & 0xFF;
-                    if (test_apic_id != base_apic_id && test_apic_id != 0xFF)
-                    {
-                        detected_cpus++;
-                        kinfo("Detected additional CPU with APIC ID: %u (heuristic)", test_apic_id);
-                    }
-                }
-            }
-
-            cpu_count = detected_cpus;
-        }
-    }
-
-    // Store detected CPU count globally
-    g_detected_cpus = cpu_count;
-
-    kinfo("SMP initialization completed - %u CPUs detected (BSP APIC ID: %u)",
-          cpu_count, apic_id);
-}
-
-// Get CPU count from ACPI/MP tables
-uint32_t arch_get_cpu_count(void)
-{
-    // Return detected CPU count from SMP initialization
-    return g_detected_cpus;
-}
-
 // ========================================
 // MISSING ARCHITECTURE FUNCTIONS
 // ========================================
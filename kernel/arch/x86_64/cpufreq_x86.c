@@ -0,0 +1,261 @@
+/*
+ * Orion Operating System - x86_64 CPU Frequency Drivers
+ *
+ * Scaling drivers for the cpufreq framework:
+ *  - intel_pstate: hardware P-states (HWP) when available, programmed with
+ *    a performance window in IA32_HWP_REQUEST, otherwise Enhanced
+ *    SpeedStep ratios in IA32_PERF_CTL. Intel performance levels are bus
+ *    ratios of 100 MHz.
+ *  - amd_cppc: Collaborative Processor Performance Control through the
+ *    CPPC MSRs of Zen processors. Performance levels are abstract and are
+ *    converted with the nominal level, whose frequency is that of P0.
+ *
+ * Both drivers only touch MSRs of the CPU they run on; the framework calls
+ * them from that CPU.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/cpufreq.h>
+#include <arch.h>
+
+// Intel MSRs
+#define MSR_PLATFORM_INFO 0xCE
+#define MSR_IA32_PERF_CTL 0x199
+#define MSR_IA32_THERM_STATUS 0x19C
+#define MSR_IA32_MISC_ENABLE 0x1A0
+#define MSR_TEMPERATURE_TARGET 0x1A2
+#define MSR_TURBO_RATIO_LIMIT 0x1AD
+#define MSR_IA32_PM_ENABLE 0x770
+#define MSR_IA32_HWP_CAPABILITIES 0x771
+#define MSR_IA32_HWP_REQUEST 0x774
+
+#define MISC_ENABLE_EIST (1ULL << 16)
+#define MISC_ENABLE_TURBO_DISABLE (1ULL << 38)
+#define THERM_STATUS_VALID (1ULL << 31)
+
+// AMD MSRs
+#define MSR_AMD_PSTATE_DEF_BASE 0xC0010064
+#define MSR_AMD_CPPC_CAP1 0xC00102B0
+#define MSR_AMD_CPPC_ENABLE 0xC00102B1
+#define MSR_AMD_CPPC_REQ 0xC00102B3
+
+// CPUID bits
+#define CPUID1_ECX_EIST (1U << 7)
+#define CPUID6_EAX_DTS (1U << 0)
+#define CPUID6_EAX_TURBO (1U << 1)
+#define CPUID6_EAX_HWP (1U << 7)
+#define CPUID80000008_EBX_CPPC (1U << 27)
+
+// Energy/performance preference written with every request: balanced
+#define EPP_BALANCED 0x80
+
+#define INTEL_BUS_KHZ 100000
+
+// driver_data layout
+#define DATA_MODE 0         // MODE_* below
+#define DATA_NOMINAL_KHZ 1  // AMD: frequency of the nominal performance level
+#define DATA_NOMINAL_PERF 2 // AMD: nominal performance level
+#define DATA_TJMAX 3         // Intel: junction temperature limit in degrees, 0 without sensor
+
+#define MODE_HWP 1
+#define MODE_EIST 2
+#define MODE_CPPC 3
+
+// ========================================
+// INTEL P-STATE
+// ========================================
+
+static uint32_t intel_ratio(uint32_t khz)
+{
+    return (khz + INTEL_BUS_KHZ / 2) / INTEL_BUS_KHZ;
+}
+
+static int intel_pstate_init(cpufreq_policy_t *policy)
+{
+    uint32_t eax, ebx, ecx, edx;
+    cpuid(6, &eax, &ebx, &ecx, &edx);
+
+    if (eax & CPUID6_EAX_DTS)
+    {
+        // The digital sensor reads degrees below TjMax; assume 100 when unset
+        uint32_t tjmax = (uint32_t)((msr_read(MSR_TEMPERATURE_TARGET) >> 16) & 0xFF);
+        policy->driver_data[DATA_TJMAX] = tjmax ? tjmax : 100;
+    }
+
+    if (eax & CPUID6_EAX_HWP)
+    {
+        msr_write(MSR_IA32_PM_ENABLE, 1);
+        uint64_t caps = msr_read(MSR_IA32_HWP_CAPABILITIES);
+        policy->cpuinfo_max_khz = (uint32_t)(caps & 0xFF) * INTEL_BUS_KHZ;
+        policy->cpuinfo_min_khz = (uint32_t)((caps >> 24) & 0xFF) * INTEL_BUS_KHZ;
+        policy->driver_data[DATA_MODE] = MODE_HWP;
+        return OR_OK;
+    }
+
+    uint32_t turbo = eax & CPUID6_EAX_TURBO;
+    cpuid(1, &eax, &ebx, &ecx, &edx);
+    if (!(ecx & CPUID1_ECX_EIST))
+    {
+        return -OR_ENODEV;
+    }
+    uint64_t misc = msr_read(MSR_IA32_MISC_ENABLE);
+    msr_write(MSR_IA32_MISC_ENABLE, misc | MISC_ENABLE_EIST);
+
+    uint64_t platform = msr_read(MSR_PLATFORM_INFO);
+    uint32_t max_ratio = (uint32_t)((platform >> 8) & 0xFF);
+    uint32_t min_ratio = (uint32_t)((platform >> 40) & 0xFF);
+    if (turbo && !(misc & MISC_ENABLE_TURBO_DISABLE))
+    {
+        // Single-core turbo ratio
+        uint32_t turbo_ratio = (uint32_t)(msr_read(MSR_TURBO_RATIO_LIMIT) & 0xFF);
+        if (turbo_ratio > max_ratio)
+        {
+            max_ratio = turbo_ratio;
+        }
+    }
+    policy->cpuinfo_max_khz = max_ratio * INTEL_BUS_KHZ;
+    policy->cpuinfo_min_khz = min_ratio * INTEL_BUS_KHZ;
+    policy->driver_data[DATA_MODE] = MODE_EIST;
+    return OR_OK;
+}
+
+static int intel_pstate_set_target(cpufreq_policy_t *policy, uint32_t target_khz, uint32_t min_khz, uint32_t max_khz)
+{
+    uint64_t target = intel_ratio(target_khz);
+
+    if (policy->driver_data[DATA_MODE] == MODE_HWP)
+    {
+        // The hardware picks the operating point inside the window,
+        // starting from the desired level
+        uint64_t request = (uint64_t)intel_ratio(min_khz) | ((uint64_t)intel_ratio(max_khz) << 8) | (target << 16) |
+                           ((uint64_t)EPP_BALANCED << 24);
+        msr_write(MSR_IA32_HWP_REQUEST, request);
+        return OR_OK;
+    }
+
+    msr_write(MSR_IA32_PERF_CTL, target << 8);
+    return OR_OK;
+}
+
+static int intel_pstate_read_temp(cpufreq_policy_t *policy, int32_t *millicelsius)
+{
+    if (policy->driver_data[DATA_TJMAX] == 0)
+    {
+        return -OR_ENODEV;
+    }
+    uint64_t status = msr_read(MSR_IA32_THERM_STATUS);
+    if (!(status & THERM_STATUS_VALID))
+    {
+        return -OR_EIO;
+    }
+    uint32_t below = (uint32_t)((status >> 16) & 0x7F);
+    *millicelsius = ((int32_t)policy->driver_data[DATA_TJMAX] - (int32_t)below) * 1000;
+    return OR_OK;
+}
+
+static const cpufreq_driver_t g_intel_pstate = {
+    .name = "intel_pstate",
+    .init = intel_pstate_init,
+    .set_target = intel_pstate_set_target,
+    .read_temp = intel_pstate_read_temp,
+};
+
+// ========================================
+// AMD CPPC
+// ========================================
+
+static uint32_t amd_perf(const cpufreq_policy_t *policy, uint32_t khz)
+{
+    uint64_t perf = (uint64_t)khz * policy->driver_data[DATA_NOMINAL_PERF] / policy->driver_data[DATA_NOMINAL_KHZ];
+    return perf > 0xFF ? 0xFF : (uint32_t)perf;
+}
+
+static uint32_t amd_khz(const cpufreq_policy_t *policy, uint32_t perf)
+{
+    return (uint32_t)((uint64_t)perf * policy->driver_data[DATA_NOMINAL_KHZ] / policy->driver_data[DATA_NOMINAL_PERF]);
+}
+
+static int amd_cppc_init(cpufreq_policy_t *policy)
+{
+    // Frequency of P0 on Zen 1-3: 200 MHz * FID / DID
+    uint64_t p0 = msr_read(MSR_AMD_PSTATE_DEF_BASE);
+    uint32_t fid = (uint32_t)(p0 & 0xFF);
+    uint32_t did = (uint32_t)((p0 >> 8) & 0x3F);
+    if (did == 0 || fid == 0)
+    {
+        return -OR_ENODEV;
+    }
+
+    msr_write(MSR_AMD_CPPC_ENABLE, 1);
+    uint64_t caps = msr_read(MSR_AMD_CPPC_CAP1);
+    uint32_t lowest = (uint32_t)(caps & 0xFF);
+    uint32_t nominal = (uint32_t)((caps >> 16) & 0xFF);
+    uint32_t highest = (uint32_t)((caps >> 24) & 0xFF);
+    if (nominal == 0 || lowest == 0)
+    {
+        return -OR_ENODEV;
+    }
+
+    policy->driver_data[DATA_MODE] = MODE_CPPC;
+    policy->driver_data[DATA_NOMINAL_KHZ] = 200000ULL * fid / did;
+    policy->driver_data[DATA_NOMINAL_PERF] = nominal;
+    policy->cpuinfo_min_khz = amd_khz(policy, lowest);
+    policy->cpuinfo_max_khz = amd_khz(policy, highest);
+    return OR_OK;
+}
+
+static int amd_cppc_set_target(cpufreq_policy_t *policy, uint32_t target_khz, uint32_t min_khz, uint32_t max_khz)
+{
+    uint64_t request = (uint64_t)amd_perf(policy, max_khz) | ((uint64_t)amd_perf(policy, min_khz) << 8) |
+                       ((uint64_t)amd_perf(policy, target_khz) << 16) | ((uint64_t)EPP_BALANCED << 24);
+    msr_write(MSR_AMD_CPPC_REQ, request);
+    return OR_OK;
+}
+
+static const cpufreq_driver_t g_amd_cppc = {
+    .name = "amd_cppc",
+    .init = amd_cppc_init,
+    .set_target = amd_cppc_set_target,
+};
+
+// ========================================
+// REGISTRATION
+// ========================================
+
+int cpufreq_x86_init(void)
+{
+    uint32_t eax, ebx, ecx, edx;
+    char vendor[13];
+
+    cpuid(0, &eax, &ebx, &ecx, &edx);
+    memcpy(vendor, &ebx, 4);
+    memcpy(vendor + 4, &edx, 4);
+    memcpy(vendor + 8, &ecx, 4);
+    vendor[12] = '\0';
+
+    if (strcmp(vendor, "GenuineIntel") == 0)
+    {
+        return cpufreq_register_driver(&g_intel_pstate);
+    }
+    if (strcmp(vendor, "AuthenticAMD") == 0)
+    {
+        cpuid(0x80000000, &eax, &ebx, &ecx, &edx);
+        if (eax < 0x80000008)
+        {
+            return -OR_ENODEV;
+        }
+        cpuid(0x80000008, &eax, &ebx, &ecx, &edx);
+        if (ebx & CPUID80000008_EBX_CPPC)
+        {
+            return cpufreq_register_driver(&g_amd_cppc);
+        }
+    }
+    return -OR_ENODEV;
+}
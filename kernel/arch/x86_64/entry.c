@@ -1,6 +1,7 @@
 // Main C entry point for Orion x86_64
 #include <orion/kernel.h>
 #include <orion/types.h>
+#include <orion/earlycon.h>
 #include "include/arch.h"
 
 // Variables externes du linker
@@ -50,10 +51,12 @@ void console_init(void) {
 
 void console_putchar(char c) {
     serial_putchar(c);
+    earlycon_putchar(c);
 }
 
 void console_puts(const char* str) {
     serial_puts(str);
+    earlycon_puts(str);
 }
 
 // Complete printf implementation for kernel
@@ -85,7 +85,9 @@ void arch_cpu_init(void);
 // Subsystem initialization
 void mmu_init(void);
 void interrupts_init(void);
+void x86_64_idt_load(void); // Load the shared IDT on an application processor
 void detect_cpu(cpu_info_t *info);
+int cpufreq_x86_init(void); // Register the CPU frequency scaling driver
 
 // Missing function declarations
 void arch_halt(void);
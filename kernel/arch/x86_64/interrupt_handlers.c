@@ -14,6 +14,9 @@
 #include <orion/types.h>
 #include <orion/scheduler.h>
 #include <orion/mm.h>
+#include <orion/aslr.h>
+#include <orion/vma.h>
+#include <orion/panic.h>
 
 // Mouse event types
 typedef enum
@@ -79,53 +82,36 @@ void windowing_system_send_mouse_event(mouse_event_t *event)
 // Page fault handling functions
 bool demand_paging_handle_fault(uint64_t fault_addr, uint64_t error_code)
 {
-    // Basic demand paging implementation
-    // This provides a foundation for full demand paging support
-
-    // Check if the fault is for a valid memory region
-    // In a real implementation, this would check the process's memory map
-
-    // For now, implement basic demand paging logic
-    bool present = !(error_code & 1);
-    bool write = error_code & 2;
-    bool user = error_code & 4;
-
-    if (!present)
+    // Only addresses inside an area of the faulting space get a page,
+    // filled the way the area says (zeroed, from its pager or device)
+    bool present = error_code & 1;
+    uint64_t access = 0;
+    if (error_code & 2)
     {
-        // Page not present - this is what demand paging handles
-        kdebug("Demand paging: Page fault at 0x%llx (not present)",
-               (unsigned long long)fault_addr);
-
-        // Get current process's address space
-        extern vm_space_t *vmm_get_current_space(void);
-        vm_space_t *current_space = vmm_get_current_space();
+        access |= VM_FLAG_WRITE;
+    }
+    if (error_code & 16)
+    {
+        access |= VM_FLAG_EXEC;
+    }
 
-        if (current_space)
-        {
-            // Try to allocate and map a new page
-            extern int vmm_allocate_page(vm_space_t * space, uint64_t addr, uint32_t flags);
-            int result = vmm_allocate_page(current_space, fault_addr,
-                                           0x02 | 0x04); // PTE_WRITE | PTE_USER
+    process_t *process = scheduler_get_current_process();
+    vm_space_t *space = (process && process->vm_space) ? process->vm_space : vmm_get_kernel_space();
 
-            if (result == OR_OK)
-            {
-                kinfo("Demand paging: Successfully allocated page at 0x%llx",
-                      (unsigned long long)fault_addr);
-                return true; // Page fault resolved
-            }
-            else
-            {
-                kerror("Demand paging: Failed to allocate page at 0x%llx (error: %d)",
-                       (unsigned long long)fault_addr, result);
-            }
-        }
-        else
-        {
-            kerror("Demand paging: No current address space available");
-        }
+    int result = vma_handle_fault(space, fault_addr, access, present);
+    if (result == OR_OK)
+    {
+        kdebug("Demand paging: populated page at 0x%llx", (unsigned long long)fault_addr);
+        return true;
     }
 
-    return false; // Not handled or failed
+    // -OR_EEXIST is an allowed write to a present page, left to the COW handler
+    if (result != -OR_EEXIST)
+    {
+        kdebug("Demand paging: fault at 0x%llx not resolved (error: %d)", (unsigned long long)fault_addr,
+               result);
+    }
+    return false;
 }
 
 bool cow_handle_fault(uint64_t fault_addr, uint64_t error_code)
@@ -137,18 +123,15 @@ bool cow_handle_fault(uint64_t fault_addr, uint64_t error_code)
         return false; // Not a COW fault
     }
 
-    // Get current process's address space
-    extern vm_space_t *vmm_get_current_space(void);
-    vm_space_t *current_space = vmm_get_current_space(); // Get current process space
-
-    if (!current_space)
+    // Only present pages are shared copy-on-write
+    if (!(error_code & 1))
     {
-        // Fallback to kernel space if no current process space
-        extern vm_space_t *vmm_get_kernel_space(void);
-        current_space = vmm_get_kernel_space();
-        kdebug("cow_handle_fault: No current process space, using kernel space");
+        return false;
     }
 
+    process_t *process = scheduler_get_current_process();
+    vm_space_t *current_space = (process && process->vm_space) ? process->vm_space : vmm_get_kernel_space();
+
     // Try to handle as COW fault
     int result = vmm_handle_cow_fault(current_space, fault_addr);
     if (result == OR_OK)
@@ -238,6 +221,37 @@ void irq_handler(uint64_t irq_num)
     send_eoi(irq_num);
 }
 
+// Classify an access to the guard pages around a stack or a DMA buffer.
+// A user process overflowing its buffer is dumped and killed; the kernel
+// overflowing one panics. Returns true when the process was killed
+static bool handle_guard_page_fault(uint64_t fault_addr, bool user, uint64_t rip)
+{
+    process_t *process = scheduler_get_current_process();
+    vm_space_t *space = (user && process) ? process->vm_space : vmm_get_kernel_space();
+    guard_region_t guard;
+    if (!space || !aslr_guard_lookup(space, fault_addr, &guard))
+    {
+        return false;
+    }
+
+    const char *kind = aslr_guard_kind_name(guard.kind);
+    kerror("Guard page hit: %s at 0x%llx (RIP: 0x%llx, PID %llu, mapping 0x%llx-0x%llx)", kind,
+           (unsigned long long)fault_addr, (unsigned long long)rip, (unsigned long long)guard.pid,
+           (unsigned long long)guard.owner_start, (unsigned long long)guard.owner_end);
+    crash_record_fault(CRASH_FAULT_GUARD_PAGE, fault_addr, guard.pid, kind);
+
+    if (!user || !process)
+    {
+        panic("Guard page hit");
+    }
+    save_core_dump(__FILE__, __LINE__, __func__, "Guard page hit");
+    extern int signal_send(process_t * target, uint32_t signal);
+    extern void sched_yield(void);
+    signal_send(process, 9); // SIGKILL
+    sched_yield();
+    return true;
+}
+
 // Page fault handler implementation
 void handle_page_fault(uint64_t error_code, uint64_t rip, uint64_t rsp)
 {
@@ -288,6 +302,12 @@ void handle_page_fault(uint64_t error_code, uint64_t rip, uint64_t rsp)
         kerror("  Instruction fetch");
     }
 
+    // Guard pages are never mapped: demand paging must not fill them in
+    if (handle_guard_page_fault(fault_addr, user, rip))
+    {
+        return; // Faulting process terminated
+    }
+
     // Try to handle page fault gracefully
     if (demand_paging_handle_fault(fault_addr, error_code))
     {
@@ -301,6 +321,8 @@ void handle_page_fault(uint64_t error_code, uint64_t rip, uint64_t rsp)
     }
 
     // If we can't handle it, panic
+    process_t *process = scheduler_get_current_process();
+    crash_record_fault(CRASH_FAULT_PAGE, fault_addr, process ? process->pid : 0, "");
     panic("Unhandled page fault");
 }
 
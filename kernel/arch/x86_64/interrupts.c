@@ -14,6 +14,7 @@
 #include <orion/kernel.h>
 #include <orion/types.h>
 #include <orion/scheduler.h>
+#include "config.h"
 
 // ========================================
 // CONSTANTS AND DEFINITIONS
@@ -141,6 +142,9 @@ extern void irq13(void); // FPU
 extern void irq14(void); // Primary ATA
 extern void irq15(void); // Secondary ATA
 
+// Kernel IPI vector (ap_trampoline.S)
+extern void x86_64_ipi_entry(void);
+
 // ========================================
 // INTERRUPT DESCRIPTOR TABLE SETUP
 // ========================================
@@ -212,6 +216,9 @@ static void idt_init(void)
     idt_set_gate(46, (uint64_t)irq14, IDT_GATE_INTERRUPT);
     idt_set_gate(47, (uint64_t)irq15, IDT_GATE_INTERRUPT);
 
+    // Cross-CPU requests from the SMP code
+    idt_set_gate(X86_64_IPI_VECTOR, (uint64_t)x86_64_ipi_entry, IDT_GATE_INTERRUPT);
+
     // Set up the IDT pointer
     idt_ptr.limit = (sizeof(idt_entry_t) * IDT_ENTRIES) - 1;
     idt_ptr.base = (uint64_t)&idt;
@@ -223,6 +230,13 @@ static void idt_init(void)
     kinfo("IDT initialized with %d entries", IDT_ENTRIES);
 }
 
+// Every CPU shares the boot CPU's IDT
+void x86_64_idt_load(void)
+{
+    extern void load_idt(uint64_t idt_ptr);
+    load_idt((uint64_t)&idt_ptr);
+}
+
 // ========================================
 // PIC CONFIGURATION
 // ========================================
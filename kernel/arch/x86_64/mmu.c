@@ -249,6 +249,13 @@ bool mmu_is_valid_addr(uint64_t vaddr) {
     return false;
 }
 
+// Device memory and the boot framebuffer are reached through the direct
+// mapping of physical memory
+void *arch_ioremap(uint64_t phys, size_t size) {
+    (void)size;
+    return (void *)PHYS_TO_VIRT(phys);
+}
+
 // Invalidate TLB for an address
 void mmu_invalidate_page(uint64_t vaddr) {
 #ifdef _MSC_VER
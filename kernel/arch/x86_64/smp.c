@@ -0,0 +1,439 @@
+/*
+ * Orion Operating System - x86_64 Multiprocessor Startup
+ *
+ * CPU discovery from the ACPI MADT and application processor startup
+ * with the INIT-SIPI-SIPI sequence. The RSDP comes from the boot
+ * information, then the EFI configuration table, with the legacy BIOS
+ * areas as a fallback.
+ *
+ * Logical CPU numbers are dense: the boot processor is CPU 0 and the
+ * other enabled local APICs follow in MADT order. arch_get_current_cpu
+ * maps the local APIC ID back to that number. Kernel IPIs all use a
+ * single fixed vector whose handler lets the core SMP code find out
+ * what was asked through its pending-message mask.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/smp.h>
+#include "include/arch.h"
+#include <orion/bootinfo.h>
+
+#define LAPIC_ID 0x20
+#define LAPIC_EOI 0xB0
+#define LAPIC_SIVR 0xF0
+#define LAPIC_ICR_LOW 0x300
+#define LAPIC_ICR_HIGH 0x310
+
+#define ICR_DELIVERY_FIXED (0U << 8)
+#define ICR_DELIVERY_INIT (5U << 8)
+#define ICR_DELIVERY_STARTUP (6U << 8)
+#define ICR_PENDING (1U << 12)
+#define ICR_LEVEL_ASSERT (1U << 14)
+
+#define MSR_APIC_BASE 0x1B
+
+#define AP_TRAMPOLINE_BASE 0x8000
+#define AP_STACK_SIZE (16 * 1024)
+#define AP_START_TIMEOUT_NS 100000000ULL // 100 ms
+
+#define MADT_LOCAL_APIC 0
+#define MADT_LOCAL_X2APIC 9
+#define MADT_ENABLED (1U << 0)
+#define MADT_ONLINE_CAPABLE (1U << 1)
+
+#pragma pack(push, 1)
+typedef struct
+{
+    char signature[8];
+    uint8_t checksum;
+    char oem_id[6];
+    uint8_t revision;
+    uint32_t rsdt_address;
+    // ACPI 2.0+
+    uint32_t length;
+    uint64_t xsdt_address;
+    uint8_t extended_checksum;
+    uint8_t reserved[3];
+} acpi_rsdp_t;
+
+typedef struct
+{
+    char signature[4];
+    uint32_t length;
+    uint8_t revision;
+    uint8_t checksum;
+    char oem_id[6];
+    char oem_table_id[8];
+    uint32_t oem_revision;
+    uint32_t creator_id;
+    uint32_t creator_revision;
+} acpi_sdt_header_t;
+
+typedef struct
+{
+    acpi_sdt_header_t header;
+    uint32_t lapic_address;
+    uint32_t flags;
+} acpi_madt_t;
+#pragma pack(pop)
+
+// Trampoline image and its parameter block (ap_trampoline.S)
+extern uint8_t ap_trampoline_start[];
+extern uint8_t ap_trampoline_end[];
+extern uint8_t ap_trampoline_params[];
+
+typedef struct
+{
+    uint64_t cr3;
+    uint64_t stack;
+    uint64_t entry;
+    uint32_t cpu;
+    uint32_t reserved;
+} ap_params_t;
+
+extern void x86_64_ipi_entry(void);
+extern uint64_t arch_get_timestamp(void);
+extern uint64_t arch_get_timestamp_frequency(void);
+extern void arch_wait_precise_ns(uint64_t nanoseconds);
+
+static volatile uint32_t *g_lapic = (volatile uint32_t *)0xFEE00000;
+static uint32_t g_cpu_count = 1;
+static uint32_t g_cpu_apic_id[MAX_CPUS];
+static uint8_t g_apic_to_cpu[256];
+static bool g_discovered = false;
+static volatile uint32_t g_ap_started;
+
+// ========================================
+// ACPI TABLES
+// ========================================
+
+static bool acpi_checksum_ok(const void *table, size_t length)
+{
+    const uint8_t *bytes = table;
+    uint8_t sum = 0;
+    for (size_t i = 0; i < length; i++)
+    {
+        sum += bytes[i];
+    }
+    return sum == 0;
+}
+
+static const acpi_rsdp_t *rsdp_scan(uintptr_t start, size_t length)
+{
+    for (uintptr_t addr = start; addr + sizeof(acpi_rsdp_t) <= start + length; addr += 16)
+    {
+        const acpi_rsdp_t *rsdp = (const acpi_rsdp_t *)addr;
+        if (memcmp(rsdp->signature, "RSD PTR ", 8) == 0 && acpi_checksum_ok(rsdp, 20))
+        {
+            return rsdp;
+        }
+    }
+    return NULL;
+}
+
+static const acpi_rsdp_t *rsdp_find(void)
+{
+    // EFI configuration table: NumberOfTableEntries at 104, the table at
+    // 112, entries being a GUID followed by a pointer
+    static const uint8_t acpi20_guid[16] = {
+        0x71, 0xe8, 0x68, 0x88, 0xf1, 0xe4, 0xd3, 0x11,
+        0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81};
+    const boot_info_t *boot = boot_info_get();
+
+    // The loader usually knows where the RSDP is
+    if (boot && boot->acpi_rsdp)
+    {
+        const acpi_rsdp_t *rsdp = (const acpi_rsdp_t *)(uintptr_t)boot->acpi_rsdp;
+        if (memcmp(rsdp->signature, "RSD PTR ", 8) == 0 && acpi_checksum_ok(rsdp, 20))
+        {
+            return rsdp;
+        }
+        kwarn("SMP: RSDP from the loader is invalid");
+    }
+
+    if (boot && boot->efi_system_table)
+    {
+        const uint8_t *st = (const uint8_t *)(uintptr_t)boot->efi_system_table;
+        uint64_t entries = *(const uint64_t *)(st + 104);
+        const uint8_t *table = (const uint8_t *)(uintptr_t)*(const uint64_t *)(st + 112);
+        for (uint64_t i = 0; table && i < entries; i++)
+        {
+            const uint8_t *entry = table + i * 24;
+            if (memcmp(entry, acpi20_guid, sizeof(acpi20_guid)) == 0)
+            {
+                return (const acpi_rsdp_t *)(uintptr_t)*(const uint64_t *)(entry + 16);
+            }
+        }
+    }
+
+    // Legacy BIOS: first KiB of the EBDA, then the BIOS ROM area
+    uintptr_t ebda = (uintptr_t)(*(volatile uint16_t *)0x40E) << 4;
+    const acpi_rsdp_t *rsdp = ebda ? rsdp_scan(ebda, 1024) : NULL;
+    return rsdp ? rsdp : rsdp_scan(0xE0000, 0x20000);
+}
+
+static const acpi_sdt_header_t *acpi_find_table(const char *signature)
+{
+    const acpi_rsdp_t *rsdp = rsdp_find();
+    if (!rsdp)
+    {
+        return NULL;
+    }
+
+    bool xsdt = rsdp->revision >= 2 && rsdp->xsdt_address;
+    const acpi_sdt_header_t *root = xsdt ? (const acpi_sdt_header_t *)(uintptr_t)rsdp->xsdt_address
+                                         : (const acpi_sdt_header_t *)(uintptr_t)rsdp->rsdt_address;
+    if (!acpi_checksum_ok(root, root->length))
+    {
+        kwarn("SMP: ACPI root table checksum mismatch");
+        return NULL;
+    }
+
+    size_t entry_size = xsdt ? 8 : 4;
+    size_t count = (root->length - sizeof(*root)) / entry_size;
+    const uint8_t *entries = (const uint8_t *)(root + 1);
+    for (size_t i = 0; i < count; i++)
+    {
+        uint64_t addr = xsdt ? *(const uint64_t *)(entries + i * 8) : *(const uint32_t *)(entries + i * 4);
+        const acpi_sdt_header_t *table = (const acpi_sdt_header_t *)(uintptr_t)addr;
+        if (table && memcmp(table->signature, signature, 4) == 0 && acpi_checksum_ok(table, table->length))
+        {
+            return table;
+        }
+    }
+    return NULL;
+}
+
+// ========================================
+// CPU DISCOVERY
+// ========================================
+
+static uint32_t lapic_read(uint32_t reg)
+{
+    return g_lapic[reg / 4];
+}
+
+static void lapic_write(uint32_t reg, uint32_t value)
+{
+    g_lapic[reg / 4] = value;
+}
+
+static uint32_t lapic_id(void)
+{
+    return lapic_read(LAPIC_ID) >> 24;
+}
+
+static void smp_add_cpu(uint32_t apic_id, uint32_t flags, uint32_t boot_apic_id)
+{
+    if (!(flags & (MADT_ENABLED | MADT_ONLINE_CAPABLE)) || apic_id == boot_apic_id)
+    {
+        return;
+    }
+    if (apic_id > 0xFF)
+    {
+        kwarn("SMP: skipping x2APIC ID %u, xAPIC mode only", apic_id);
+        return;
+    }
+    if (g_cpu_count >= MAX_CPUS)
+    {
+        return;
+    }
+    g_cpu_apic_id[g_cpu_count] = apic_id;
+    g_apic_to_cpu[apic_id] = (uint8_t)g_cpu_count;
+    g_cpu_count++;
+}
+
+static void smp_discover(void)
+{
+    g_discovered = true;
+
+    uint64_t apic_base = msr_read(MSR_APIC_BASE);
+    g_lapic = (volatile uint32_t *)(uintptr_t)(apic_base & 0xFFFFF000);
+    uint32_t boot_apic_id = lapic_id();
+    g_cpu_apic_id[0] = boot_apic_id;
+    g_apic_to_cpu[boot_apic_id] = 0;
+
+    const acpi_madt_t *madt = (const acpi_madt_t *)acpi_find_table("APIC");
+    if (!madt)
+    {
+        kwarn("SMP: no ACPI MADT, running on the boot CPU only");
+        return;
+    }
+
+    const uint8_t *entry = (const uint8_t *)(madt + 1);
+    const uint8_t *end = (const uint8_t *)madt + madt->header.length;
+    while (entry + 2 <= end && entry[1] >= 2 && entry + entry[1] <= end)
+    {
+        if (entry[0] == MADT_LOCAL_APIC)
+        {
+            smp_add_cpu(entry[3], *(const uint32_t *)(entry + 4), boot_apic_id);
+        }
+        else if (entry[0] == MADT_LOCAL_X2APIC)
+        {
+            smp_add_cpu(*(const uint32_t *)(entry + 4), *(const uint32_t *)(entry + 8), boot_apic_id);
+        }
+        entry += entry[1];
+    }
+
+    kinfo("SMP: MADT lists %u CPUs, boot CPU APIC ID %u", g_cpu_count, boot_apic_id);
+}
+
+uint32_t arch_get_cpu_count(void)
+{
+    if (!g_discovered)
+    {
+        smp_discover();
+    }
+    return g_cpu_count;
+}
+
+uint32_t arch_get_current_cpu(void)
+{
+    // CPU 0 until discovery has filled in the APIC ID map
+    return g_discovered ? g_apic_to_cpu[lapic_id()] : 0;
+}
+
+// ========================================
+// INTER-PROCESSOR INTERRUPTS
+// ========================================
+
+static void lapic_send_icr(uint32_t apic_id, uint32_t command)
+{
+    while (lapic_read(LAPIC_ICR_LOW) & ICR_PENDING)
+    {
+        arch_pause();
+    }
+    lapic_write(LAPIC_ICR_HIGH, apic_id << 24);
+    lapic_write(LAPIC_ICR_LOW, command);
+}
+
+int arch_smp_send_ipi(uint32_t cpu)
+{
+    if (cpu >= g_cpu_count)
+    {
+        return -OR_EINVAL;
+    }
+    unsigned long flags;
+    __asm__ volatile("pushfq; popq %0; cli" : "=r"(flags)::"memory");
+    lapic_send_icr(g_cpu_apic_id[cpu], ICR_DELIVERY_FIXED | ICR_LEVEL_ASSERT | X86_64_IPI_VECTOR);
+    __asm__ volatile("pushq %0; popfq" ::"r"(flags) : "memory", "cc");
+    return OR_OK;
+}
+
+// Called from x86_64_ipi_entry with interrupts masked
+void x86_64_ipi_interrupt(void)
+{
+    smp_handle_ipi();
+    lapic_write(LAPIC_EOI, 0);
+}
+
+void arch_tlb_flush_local(uint64_t vaddr, uint64_t pages)
+{
+    if (pages == SMP_TLB_FLUSH_ALL || pages > 64)
+    {
+        write_cr3(read_cr3());
+        return;
+    }
+    for (uint64_t i = 0; i < pages; i++)
+    {
+        __asm__ volatile("invlpg (%0)" ::"r"(vaddr + i * PAGE_SIZE) : "memory");
+    }
+}
+
+// ========================================
+// APPLICATION PROCESSOR STARTUP
+// ========================================
+
+// First C code run by an application processor, on its own stack
+void x86_64_ap_main(uint32_t cpu)
+{
+    x86_64_idt_load();
+
+    // Software-enable the local APIC, spurious vector 0xFF
+    lapic_write(LAPIC_SIVR, lapic_read(LAPIC_SIVR) | 0x1FF);
+
+    __atomic_store_n(&g_ap_started, 1, __ATOMIC_RELEASE);
+    smp_secondary_main(cpu);
+}
+
+static bool smp_start_ap(uint32_t cpu, ap_params_t *params)
+{
+    void *stack = kmalloc(AP_STACK_SIZE);
+    if (!stack)
+    {
+        kerror("SMP: no memory for CPU %u stack", cpu);
+        return false;
+    }
+
+    params->cr3 = read_cr3();
+    params->stack = ((uintptr_t)stack + AP_STACK_SIZE) & ~0xFULL;
+    params->entry = (uint64_t)(uintptr_t)x86_64_ap_main;
+    params->cpu = cpu;
+    __atomic_store_n(&g_ap_started, 0, __ATOMIC_RELEASE);
+
+    uint32_t apic_id = g_cpu_apic_id[cpu];
+    lapic_send_icr(apic_id, ICR_DELIVERY_INIT | ICR_LEVEL_ASSERT);
+    arch_wait_precise_ns(10000000); // 10 ms
+
+    for (int attempt = 0; attempt < 2; attempt++)
+    {
+        lapic_send_icr(apic_id, ICR_DELIVERY_STARTUP | (AP_TRAMPOLINE_BASE >> 12));
+        arch_wait_precise_ns(200000); // 200 us
+        if (__atomic_load_n(&g_ap_started, __ATOMIC_ACQUIRE))
+        {
+            return true;
+        }
+    }
+
+    uint64_t start = arch_get_timestamp();
+    uint64_t timeout = AP_START_TIMEOUT_NS * arch_get_timestamp_frequency() / 1000000000ULL;
+    while (arch_get_timestamp() - start < timeout)
+    {
+        if (__atomic_load_n(&g_ap_started, __ATOMIC_ACQUIRE))
+        {
+            return true;
+        }
+        arch_pause();
+    }
+
+    kfree(stack);
+    kwarn("SMP: CPU %u (APIC ID %u) did not start", cpu, apic_id);
+    return false;
+}
+
+int arch_smp_boot(void)
+{
+    if (read_cr3() >> 32)
+    {
+        kerror("SMP: page tables above 4 GiB, cannot start application processors");
+        return -OR_EINVAL;
+    }
+
+    // The trampoline stays below 1 MiB for as long as processors start
+    size_t size = (size_t)(ap_trampoline_end - ap_trampoline_start);
+    memcpy((void *)AP_TRAMPOLINE_BASE, ap_trampoline_start, size);
+    ap_params_t *params = (ap_params_t *)(AP_TRAMPOLINE_BASE + (ap_trampoline_params - ap_trampoline_start));
+
+    uint32_t started = 0;
+    for (uint32_t cpu = 1; cpu < g_cpu_count; cpu++)
+    {
+        if (smp_start_ap(cpu, params))
+        {
+            // Wait for it to join before reusing the parameter block
+            while (!smp_cpu_is_online(cpu))
+            {
+                arch_pause();
+            }
+            started++;
+        }
+    }
+    return started == g_cpu_count - 1 ? OR_OK : -OR_ETIMEDOUT;
+}
@@ -17,11 +17,6 @@
 // CPU MANAGEMENT STUBS
 // ========================================
 
-// Get current CPU ID (single CPU for now)
-uint32_t arch_get_current_cpu(void) {
-    return 0; // Always CPU 0 for now
-}
-
 // Initialize interrupt subsystem
 void arch_interrupt_init(void) {
     // This is now implemented in interrupts.c
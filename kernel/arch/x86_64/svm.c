@@ -0,0 +1,808 @@
+/*
+ * Orion Operating System - AMD SVM Backend
+ *
+ * Hypervisor backend for AMD-V with nested paging. Unlike a VMCS, a VMCB
+ * is plain memory, so a vCPU may move between CPUs without ceremony; each
+ * has an ASID of its own and its TLB entries are flushed by the next VMRUN
+ * when hv_tlb_flush_needed asks for it. The guest state not kept in the
+ * VMCB (segment registers hidden parts, syscall MSRs) is switched with
+ * VMLOAD and VMSAVE around VMRUN in virt_asm.S.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/hypervisor.h>
+#include <arch.h>
+#include "virt.h"
+
+#define MSR_VM_CR 0xC0010114
+#define MSR_VM_HSAVE_PA 0xC0010117
+#define VM_CR_SVMDIS (1ULL << 4)
+
+#define CPUID80000001_ECX_SVM (1U << 2)
+#define CPUID8000000A_EDX_NP (1U << 0)
+
+// VMCB control area
+#define VMCB_INTERCEPT_MISC1 0x00C
+#define VMCB_INTERCEPT_MISC2 0x010
+#define VMCB_IOPM_BASE 0x040
+#define VMCB_MSRPM_BASE 0x048
+#define VMCB_ASID 0x058
+#define VMCB_TLB_CONTROL 0x05C
+#define VMCB_V_INTR 0x060
+#define VMCB_INTERRUPT_SHADOW 0x068
+#define VMCB_EXIT_CODE 0x070
+#define VMCB_EXIT_INFO1 0x078
+#define VMCB_EXIT_INFO2 0x080
+#define VMCB_EXIT_INT_INFO 0x088
+#define VMCB_NP_ENABLE 0x090
+#define VMCB_EVENT_INJECT 0x0A8
+#define VMCB_N_CR3 0x0B0
+#define VMCB_NEXT_RIP 0x0C8
+
+#define INTERCEPT_INTR (1U << 0)
+#define INTERCEPT_NMI (1U << 1)
+#define INTERCEPT_VINTR (1U << 4)
+#define INTERCEPT_CPUID (1U << 18)
+#define INTERCEPT_HLT (1U << 24)
+#define INTERCEPT_IOIO (1U << 27)
+#define INTERCEPT_MSR (1U << 28)
+#define INTERCEPT_SHUTDOWN (1U << 31)
+#define INTERCEPT_VMRUN (1U << 0)
+#define INTERCEPT_VMMCALL (1U << 1)
+#define INTERCEPT_VMLOAD (1U << 2)
+#define INTERCEPT_VMSAVE (1U << 3)
+#define INTERCEPT_STGI (1U << 4)
+#define INTERCEPT_CLGI (1U << 5)
+#define INTERCEPT_SKINIT (1U << 6)
+
+#define V_INTR_IRQ (1ULL << 8)
+#define V_INTR_PRIORITY_SHIFT 16
+#define V_INTR_IGNORE_TPR (1ULL << 20)
+#define V_INTR_MASKING (1ULL << 24)
+
+#define TLB_CONTROL_FLUSH_ASID 3
+
+// Event injection and exit interrupt information
+#define EVENT_VALID (1U << 31)
+#define EVENT_ERROR (1U << 11)
+#define EVENT_TYPE_EXTERNAL (0U << 8)
+#define EVENT_TYPE_EXCEPTION (3U << 8)
+
+// VMCB state save area
+#define VMCB_SAVE 0x400
+#define SAVE_ES 0x000 // Then CS, SS, DS, FS, GS, GDTR, LDTR, IDTR, TR by 16
+#define SAVE_CS 0x010
+#define SAVE_SS 0x020
+#define SAVE_DS 0x030
+#define SAVE_FS 0x040
+#define SAVE_GS 0x050
+#define SAVE_GDTR 0x060
+#define SAVE_LDTR 0x070
+#define SAVE_IDTR 0x080
+#define SAVE_TR 0x090
+#define SAVE_CPL 0x0CB
+#define SAVE_EFER 0x0D0
+#define SAVE_CR4 0x148
+#define SAVE_CR3 0x150
+#define SAVE_CR0 0x158
+#define SAVE_DR7 0x160
+#define SAVE_DR6 0x168
+#define SAVE_RFLAGS 0x170
+#define SAVE_RIP 0x178
+#define SAVE_RSP 0x1D8
+#define SAVE_RAX 0x1F8
+#define SAVE_STAR 0x200
+#define SAVE_LSTAR 0x208
+#define SAVE_CSTAR 0x210
+#define SAVE_SFMASK 0x218
+#define SAVE_KERNEL_GS_BASE 0x220
+#define SAVE_SYSENTER_CS 0x228
+#define SAVE_SYSENTER_ESP 0x230
+#define SAVE_SYSENTER_EIP 0x238
+#define SAVE_CR2 0x240
+#define SAVE_G_PAT 0x268
+
+// Exit codes
+#define SVM_EXIT_INTR 0x60
+#define SVM_EXIT_NMI 0x61
+#define SVM_EXIT_VINTR 0x64
+#define SVM_EXIT_CPUID 0x72
+#define SVM_EXIT_HLT 0x78
+#define SVM_EXIT_IOIO 0x7B
+#define SVM_EXIT_MSR 0x7C
+#define SVM_EXIT_SHUTDOWN 0x7F
+#define SVM_EXIT_VMRUN 0x80
+#define SVM_EXIT_SKINIT 0x86
+#define SVM_EXIT_NPF 0x400
+#define SVM_EXIT_INVALID ((uint64_t)-1)
+
+// Nested page table entries
+#define NPT_PRESENT (1ULL << 0)
+#define NPT_WRITE (1ULL << 1)
+#define NPT_USER (1ULL << 2)
+#define NPT_NX (1ULL << 63)
+#define NPT_TABLE (NPT_PRESENT | NPT_WRITE | NPT_USER)
+
+#define IOPM_PAGES 3
+#define MSRPM_PAGES 2
+
+typedef struct svm_vcpu
+{
+    virt_fpu_t fpu; // First, see virt.h
+    uint64_t vmcb;  // Physical page
+    hv_regs_t regs; // RAX, RSP, RIP and RFLAGS live in the VMCB
+    uint32_t asid;
+    uint32_t step; // Length of the instruction awaiting completion
+} svm_vcpu_t;
+
+typedef struct svm_vm
+{
+    uint64_t npt_root;
+} svm_vm_t;
+
+static uint64_t g_iopm;  // Every port intercepted
+static uint64_t g_msrpm; // Every MSR intercepted
+static uint32_t g_max_asid;
+static uint32_t g_next_asid = 1;
+static uint64_t g_host_save[MAX_CPUS];
+static spinlock_t g_asid_lock = SPINLOCK_INIT;
+
+// ========================================
+// VMCB ACCESS
+// ========================================
+
+static inline uint8_t *vmcb_of(const svm_vcpu_t *v)
+{
+    return (uint8_t *)PHYS_TO_VIRT(v->vmcb);
+}
+
+static inline uint64_t vmcb_read(const svm_vcpu_t *v, uint32_t offset)
+{
+    return *(uint64_t *)(vmcb_of(v) + offset);
+}
+
+static inline void vmcb_write(svm_vcpu_t *v, uint32_t offset, uint64_t value)
+{
+    *(uint64_t *)(vmcb_of(v) + offset) = value;
+}
+
+static inline uint32_t vmcb_read32(const svm_vcpu_t *v, uint32_t offset)
+{
+    return *(uint32_t *)(vmcb_of(v) + offset);
+}
+
+static inline void vmcb_write32(svm_vcpu_t *v, uint32_t offset, uint32_t value)
+{
+    *(uint32_t *)(vmcb_of(v) + offset) = value;
+}
+
+// Segments are selector (16), attributes (16), limit (32) and base (64),
+// with the attributes packed without the gap of hv_segment_t
+static void svm_write_segment(svm_vcpu_t *v, uint32_t offset, const hv_segment_t *segment)
+{
+    uint8_t *vmcb = vmcb_of(v) + VMCB_SAVE + offset;
+    *(uint16_t *)vmcb = segment->selector;
+    *(uint16_t *)(vmcb + 2) = (segment->attributes & 0xFF) | ((segment->attributes >> 4) & 0xF00);
+    *(uint32_t *)(vmcb + 4) = segment->limit;
+    *(uint64_t *)(vmcb + 8) = segment->base;
+}
+
+static void svm_read_segment(const svm_vcpu_t *v, uint32_t offset, hv_segment_t *segment)
+{
+    const uint8_t *vmcb = vmcb_of(v) + VMCB_SAVE + offset;
+    uint16_t attributes = *(const uint16_t *)(vmcb + 2);
+    segment->selector = *(const uint16_t *)vmcb;
+    segment->attributes = (attributes & 0xFF) | ((attributes & 0xF00) << 4);
+    segment->limit = *(const uint32_t *)(vmcb + 4);
+    segment->base = *(const uint64_t *)(vmcb + 8);
+}
+
+static inline uint64_t save_read(const svm_vcpu_t *v, uint32_t offset)
+{
+    return vmcb_read(v, VMCB_SAVE + offset);
+}
+
+static inline void save_write(svm_vcpu_t *v, uint32_t offset, uint64_t value)
+{
+    vmcb_write(v, VMCB_SAVE + offset, value);
+}
+
+// ========================================
+// SET UP
+// ========================================
+
+static int svm_cpu_enable(void)
+{
+    // One page for VMRUN itself, one for VMSAVE of the host
+    uint64_t hsave = pmm_alloc_page();
+    uint64_t save = pmm_alloc_page();
+    if (!hsave || !save)
+    {
+        if (hsave)
+        {
+            pmm_free_page(hsave);
+        }
+        if (save)
+        {
+            pmm_free_page(save);
+        }
+        return -OR_ENOMEM;
+    }
+    memset((void *)PHYS_TO_VIRT(hsave), 0, PAGE_SIZE);
+    memset((void *)PHYS_TO_VIRT(save), 0, PAGE_SIZE);
+
+    msr_write(MSR_EFER, msr_read(MSR_EFER) | EFER_SVME);
+    msr_write(MSR_VM_HSAVE_PA, hsave);
+    g_host_save[arch_get_current_cpu()] = save;
+    return OR_OK;
+}
+
+static int svm_vm_init(hv_vm_t *vm)
+{
+    svm_vm_t *state = kmalloc(sizeof(*state));
+    if (!state)
+    {
+        return -OR_ENOMEM;
+    }
+    // Every VMCB of the VM points to the root
+    state->npt_root = pmm_alloc_page();
+    if (!state->npt_root)
+    {
+        kfree(state);
+        return -OR_ENOMEM;
+    }
+    memset((void *)PHYS_TO_VIRT(state->npt_root), 0, PAGE_SIZE);
+    vm->backend = state;
+    return OR_OK;
+}
+
+static void svm_vm_destroy(hv_vm_t *vm)
+{
+    svm_vm_t *state = vm->backend;
+    if (state)
+    {
+        virt_pt_free(state->npt_root);
+        kfree(state);
+        vm->backend = NULL;
+    }
+}
+
+static int svm_map(hv_vm_t *vm, uint64_t gpa, uint64_t hpa, uint32_t flags)
+{
+    svm_vm_t *state = vm->backend;
+    uint64_t entry = hpa | NPT_PRESENT | NPT_USER;
+    entry |= (flags & HV_MEM_WRITE) ? NPT_WRITE : 0;
+    entry |= (flags & HV_MEM_EXEC) ? 0 : NPT_NX;
+    return virt_pt_map(&state->npt_root, gpa, entry, NPT_TABLE);
+}
+
+static uint64_t svm_unmap(hv_vm_t *vm, uint64_t gpa)
+{
+    svm_vm_t *state = vm->backend;
+    return virt_pt_unmap(state->npt_root, gpa) & VIRT_PT_ADDR;
+}
+
+// ========================================
+// GUEST STATE
+// ========================================
+
+static void svm_write_state(svm_vcpu_t *v, const hv_vcpu_state_t *state)
+{
+    v->regs = state->regs;
+    save_write(v, SAVE_RAX, state->regs.rax);
+    save_write(v, SAVE_RSP, state->regs.rsp);
+    save_write(v, SAVE_RIP, state->regs.rip);
+    save_write(v, SAVE_RFLAGS, state->regs.rflags | 0x2);
+
+    save_write(v, SAVE_CR0, state->cr0);
+    save_write(v, SAVE_CR2, state->cr2);
+    save_write(v, SAVE_CR3, state->cr3);
+    save_write(v, SAVE_CR4, state->cr4);
+    // SVME must stay set in the guest EFER; it reads back cleared
+    uint64_t efer = state->efer;
+    efer = ((efer & EFER_LME) && (state->cr0 & CR0_PG)) ? (efer | EFER_LMA) : (efer & ~EFER_LMA);
+    save_write(v, SAVE_EFER, efer | EFER_SVME);
+
+    svm_write_segment(v, SAVE_ES, &state->es);
+    svm_write_segment(v, SAVE_CS, &state->cs);
+    svm_write_segment(v, SAVE_SS, &state->ss);
+    svm_write_segment(v, SAVE_DS, &state->ds);
+    svm_write_segment(v, SAVE_FS, &state->fs);
+    svm_write_segment(v, SAVE_GS, &state->gs);
+    svm_write_segment(v, SAVE_LDTR, &state->ldtr);
+    svm_write_segment(v, SAVE_TR, &state->tr);
+    hv_segment_t gdt = {.base = state->gdt.base, .limit = state->gdt.limit};
+    hv_segment_t idt = {.base = state->idt.base, .limit = state->idt.limit};
+    svm_write_segment(v, SAVE_GDTR, &gdt);
+    svm_write_segment(v, SAVE_IDTR, &idt);
+    // CPL is the DPL of SS, 0 in real mode
+    *(vmcb_of(v) + VMCB_SAVE + SAVE_CPL) = (state->cr0 & CR0_PE) ? (state->ss.attributes >> 5) & 3 : 0;
+
+    save_write(v, SAVE_STAR, state->star);
+    save_write(v, SAVE_LSTAR, state->lstar);
+    save_write(v, SAVE_CSTAR, state->cstar);
+    save_write(v, SAVE_SFMASK, state->sfmask);
+    save_write(v, SAVE_KERNEL_GS_BASE, state->kernel_gs_base);
+    save_write(v, SAVE_SYSENTER_CS, state->sysenter_cs);
+    save_write(v, SAVE_SYSENTER_ESP, state->sysenter_esp);
+    save_write(v, SAVE_SYSENTER_EIP, state->sysenter_eip);
+
+    vmcb_write(v, VMCB_INTERRUPT_SHADOW, 0);
+    vmcb_write(v, VMCB_EVENT_INJECT, 0);
+    v->step = 0;
+}
+
+static void svm_read_state(const svm_vcpu_t *v, hv_vcpu_state_t *state)
+{
+    state->regs = v->regs;
+    state->regs.rax = save_read(v, SAVE_RAX);
+    state->regs.rsp = save_read(v, SAVE_RSP);
+    state->regs.rip = save_read(v, SAVE_RIP);
+    state->regs.rflags = save_read(v, SAVE_RFLAGS);
+
+    state->cr0 = save_read(v, SAVE_CR0);
+    state->cr2 = save_read(v, SAVE_CR2);
+    state->cr3 = save_read(v, SAVE_CR3);
+    state->cr4 = save_read(v, SAVE_CR4);
+    state->efer = save_read(v, SAVE_EFER) & ~EFER_SVME;
+
+    svm_read_segment(v, SAVE_ES, &state->es);
+    svm_read_segment(v, SAVE_CS, &state->cs);
+    svm_read_segment(v, SAVE_SS, &state->ss);
+    svm_read_segment(v, SAVE_DS, &state->ds);
+    svm_read_segment(v, SAVE_FS, &state->fs);
+    svm_read_segment(v, SAVE_GS, &state->gs);
+    svm_read_segment(v, SAVE_LDTR, &state->ldtr);
+    svm_read_segment(v, SAVE_TR, &state->tr);
+    hv_segment_t table;
+    svm_read_segment(v, SAVE_GDTR, &table);
+    state->gdt.base = table.base;
+    state->gdt.limit = (uint16_t)table.limit;
+    svm_read_segment(v, SAVE_IDTR, &table);
+    state->idt.base = table.base;
+    state->idt.limit = (uint16_t)table.limit;
+
+    state->star = save_read(v, SAVE_STAR);
+    state->lstar = save_read(v, SAVE_LSTAR);
+    state->cstar = save_read(v, SAVE_CSTAR);
+    state->sfmask = save_read(v, SAVE_SFMASK);
+    state->kernel_gs_base = save_read(v, SAVE_KERNEL_GS_BASE);
+    state->sysenter_cs = save_read(v, SAVE_SYSENTER_CS);
+    state->sysenter_esp = save_read(v, SAVE_SYSENTER_ESP);
+    state->sysenter_eip = save_read(v, SAVE_SYSENTER_EIP);
+}
+
+static void svm_get_state(hv_vcpu_t *vcpu, hv_vcpu_state_t *state)
+{
+    svm_read_state(vcpu->backend, state);
+}
+
+static int svm_set_state(hv_vcpu_t *vcpu, const hv_vcpu_state_t *state)
+{
+    svm_write_state(vcpu->backend, state);
+    return OR_OK;
+}
+
+// ========================================
+// VCPUS
+// ========================================
+
+static uint32_t svm_asid_alloc(void)
+{
+    uint32_t asid = 0;
+    spinlock_lock(&g_asid_lock);
+    if (g_next_asid < g_max_asid)
+    {
+        asid = g_next_asid++;
+    }
+    spinlock_unlock(&g_asid_lock);
+    return asid;
+}
+
+static int svm_vcpu_init(hv_vcpu_t *vcpu)
+{
+    uint64_t page = pmm_alloc_page();
+    if (!page)
+    {
+        return -OR_ENOMEM;
+    }
+    svm_vcpu_t *v = (svm_vcpu_t *)PHYS_TO_VIRT(page);
+    memset(v, 0, sizeof(*v));
+    virt_fpu_init(&v->fpu);
+    vcpu->backend = v;
+    v->vmcb = pmm_alloc_page();
+    if (!v->vmcb)
+    {
+        return -OR_ENOMEM;
+    }
+    // ASIDs are not reused: the VM count bounds what a boot consumes
+    v->asid = svm_asid_alloc();
+    if (!v->asid)
+    {
+        return -OR_ENOSPC;
+    }
+    memset(vmcb_of(v), 0, PAGE_SIZE);
+
+    svm_vm_t *vm = vcpu->vm->backend;
+    vmcb_write32(v, VMCB_INTERCEPT_MISC1, INTERCEPT_INTR | INTERCEPT_NMI | INTERCEPT_CPUID | INTERCEPT_HLT |
+                                              INTERCEPT_IOIO | INTERCEPT_MSR | INTERCEPT_SHUTDOWN);
+    vmcb_write32(v, VMCB_INTERCEPT_MISC2, INTERCEPT_VMRUN | INTERCEPT_VMMCALL | INTERCEPT_VMLOAD | INTERCEPT_VMSAVE |
+                                              INTERCEPT_STGI | INTERCEPT_CLGI | INTERCEPT_SKINIT);
+    vmcb_write(v, VMCB_IOPM_BASE, g_iopm);
+    vmcb_write(v, VMCB_MSRPM_BASE, g_msrpm);
+    vmcb_write32(v, VMCB_ASID, v->asid);
+    vmcb_write(v, VMCB_V_INTR, V_INTR_MASKING);
+    vmcb_write(v, VMCB_NP_ENABLE, 1);
+    vmcb_write(v, VMCB_N_CR3, vm->npt_root);
+    save_write(v, SAVE_G_PAT, PAT_DEFAULT);
+    save_write(v, SAVE_DR6, 0xFFFF0FF0);
+    save_write(v, SAVE_DR7, 0x400);
+
+    hv_vcpu_state_t reset;
+    virt_reset_state(&reset);
+    svm_write_state(v, &reset);
+    return OR_OK;
+}
+
+static void svm_vcpu_destroy(hv_vcpu_t *vcpu)
+{
+    svm_vcpu_t *v = vcpu->backend;
+    if (!v)
+    {
+        return;
+    }
+    if (v->vmcb)
+    {
+        pmm_free_page(v->vmcb);
+    }
+    pmm_free_page(VIRT_TO_PHYS(v));
+    vcpu->backend = NULL;
+}
+
+// ========================================
+// RUNNING
+// ========================================
+
+static void svm_inject_exception(svm_vcpu_t *v, uint32_t vector, bool has_error, uint32_t error)
+{
+    uint64_t event = vector | EVENT_TYPE_EXCEPTION | EVENT_VALID | (has_error ? EVENT_ERROR : 0);
+    vmcb_write(v, VMCB_EVENT_INJECT, event | ((uint64_t)error << 32));
+}
+
+static void svm_step(svm_vcpu_t *v, uint32_t length)
+{
+    save_write(v, SAVE_RIP, save_read(v, SAVE_RIP) + length);
+    vmcb_write(v, VMCB_INTERRUPT_SHADOW, 0);
+}
+
+// Inject the highest interrupt if the guest can take it, else request a
+// virtual interrupt, which exits as soon as it could be delivered
+static void svm_inject(hv_vcpu_t *vcpu, svm_vcpu_t *v)
+{
+    uint32_t misc1 = vmcb_read32(v, VMCB_INTERCEPT_MISC1) & ~INTERCEPT_VINTR;
+    uint64_t v_intr = V_INTR_MASKING;
+    int vector = hv_irq_next(vcpu);
+
+    if (vector >= 0 && !(vmcb_read(v, VMCB_EVENT_INJECT) & EVENT_VALID))
+    {
+        bool shadow = vmcb_read(v, VMCB_INTERRUPT_SHADOW) & 1;
+        if ((save_read(v, SAVE_RFLAGS) & RFLAGS_IF) && !shadow)
+        {
+            vmcb_write(v, VMCB_EVENT_INJECT, (uint32_t)vector | EVENT_TYPE_EXTERNAL | EVENT_VALID);
+            hv_irq_ack(vcpu, (uint32_t)vector);
+        }
+        else
+        {
+            misc1 |= INTERCEPT_VINTR;
+            v_intr |= V_INTR_IRQ | V_INTR_IGNORE_TPR | (0xFULL << V_INTR_PRIORITY_SHIFT);
+        }
+    }
+    else if (vector >= 0)
+    {
+        // After the pending event
+        misc1 |= INTERCEPT_VINTR;
+        v_intr |= V_INTR_IRQ | V_INTR_IGNORE_TPR | (0xFULL << V_INTR_PRIORITY_SHIFT);
+    }
+    vmcb_write32(v, VMCB_INTERCEPT_MISC1, misc1);
+    vmcb_write(v, VMCB_V_INTR, v_intr);
+}
+
+static void svm_complete(hv_vcpu_t *vcpu, svm_vcpu_t *v)
+{
+    const hv_exit_t *exit = &vcpu->exit;
+    const hv_exit_t *done = &vcpu->completion;
+    uint64_t rax = save_read(v, SAVE_RAX);
+
+    if (exit->reason == HV_EXIT_IO)
+    {
+        if (!(exit->io.flags & HV_IO_WRITE))
+        {
+            uint64_t data = done->io.data;
+            switch (exit->io.size)
+            {
+            case 1:
+                rax = (rax & ~0xFFULL) | (data & 0xFF);
+                break;
+            case 2:
+                rax = (rax & ~0xFFFFULL) | (data & 0xFFFF);
+                break;
+            default:
+                rax = (uint32_t)data;
+                break;
+            }
+            save_write(v, SAVE_RAX, rax);
+        }
+        svm_step(v, v->step);
+    }
+    else if (exit->reason == HV_EXIT_MSR)
+    {
+        if (done->msr.flags & HV_MSR_FAULT)
+        {
+            svm_inject_exception(v, VECTOR_GP, true, 0);
+            return;
+        }
+        if (!(exit->msr.flags & HV_MSR_WRITE))
+        {
+            save_write(v, SAVE_RAX, (uint32_t)done->msr.value);
+            v->regs.rdx = done->msr.value >> 32;
+        }
+        svm_step(v, v->step);
+    }
+}
+
+// The VMCB keeps every MSR the kernel handles except the TSC and EFER.SVME
+static uint64_t *svm_msr_slot(svm_vcpu_t *v, uint32_t index)
+{
+    uint32_t offset;
+    switch (index)
+    {
+    case MSR_STAR:
+        offset = SAVE_STAR;
+        break;
+    case MSR_LSTAR:
+        offset = SAVE_LSTAR;
+        break;
+    case MSR_CSTAR:
+        offset = SAVE_CSTAR;
+        break;
+    case MSR_SFMASK:
+        offset = SAVE_SFMASK;
+        break;
+    case MSR_KERNEL_GS_BASE:
+        offset = SAVE_KERNEL_GS_BASE;
+        break;
+    case MSR_IA32_SYSENTER_CS:
+        offset = SAVE_SYSENTER_CS;
+        break;
+    case MSR_IA32_SYSENTER_ESP:
+        offset = SAVE_SYSENTER_ESP;
+        break;
+    case MSR_IA32_SYSENTER_EIP:
+        offset = SAVE_SYSENTER_EIP;
+        break;
+    case MSR_IA32_PAT:
+        offset = SAVE_G_PAT;
+        break;
+    case MSR_FS_BASE:
+        offset = SAVE_FS + 8;
+        break;
+    case MSR_GS_BASE:
+        offset = SAVE_GS + 8;
+        break;
+    default:
+        return NULL;
+    }
+    return (uint64_t *)(vmcb_of(v) + VMCB_SAVE + offset);
+}
+
+static uint32_t svm_msr(svm_vcpu_t *v, hv_exit_t *exit)
+{
+    bool write = vmcb_read(v, VMCB_EXIT_INFO1) & 1;
+    uint32_t index = (uint32_t)v->regs.rcx;
+    uint64_t value = (v->regs.rdx << 32) | (uint32_t)save_read(v, SAVE_RAX);
+    uint64_t *slot = svm_msr_slot(v, index);
+
+    if (slot)
+    {
+        if (write)
+        {
+            *slot = value;
+        }
+        value = *slot;
+    }
+    else if (index == MSR_EFER)
+    {
+        if (write)
+        {
+            uint64_t efer = value & ~EFER_LMA;
+            if ((efer & EFER_LME) && (save_read(v, SAVE_CR0) & CR0_PG))
+            {
+                efer |= EFER_LMA;
+            }
+            save_write(v, SAVE_EFER, efer | EFER_SVME);
+        }
+        value = save_read(v, SAVE_EFER) & ~EFER_SVME;
+    }
+    else if (index == MSR_IA32_TSC)
+    {
+        value = arch_get_rdtsc();
+    }
+    else
+    {
+        exit->msr.index = index;
+        exit->msr.flags = write ? HV_MSR_WRITE : 0;
+        exit->msr.value = write ? value : 0;
+        v->step = 2;
+        return HV_EXIT_MSR;
+    }
+
+    if (!write)
+    {
+        save_write(v, SAVE_RAX, (uint32_t)value);
+        v->regs.rdx = value >> 32;
+    }
+    svm_step(v, 2);
+    return HV_EXIT_NONE;
+}
+
+static uint32_t svm_handle_exit(hv_vcpu_t *vcpu, svm_vcpu_t *v, hv_exit_t *exit)
+{
+    uint64_t code = vmcb_read(v, VMCB_EXIT_CODE);
+    uint64_t info1 = vmcb_read(v, VMCB_EXIT_INFO1);
+    uint64_t info2 = vmcb_read(v, VMCB_EXIT_INFO2);
+
+    // An event whose delivery the exit interrupted is delivered again
+    uint64_t interrupted = vmcb_read(v, VMCB_EXIT_INT_INFO);
+    if (interrupted & EVENT_VALID)
+    {
+        vmcb_write(v, VMCB_EVENT_INJECT, interrupted);
+    }
+
+    switch (code)
+    {
+    case SVM_EXIT_INTR:
+    case SVM_EXIT_NMI:
+        // Taken by the host once GIF is set again
+        return HV_EXIT_NONE;
+    case SVM_EXIT_VINTR:
+        return HV_EXIT_NONE;
+    case SVM_EXIT_SHUTDOWN:
+        return HV_EXIT_SHUTDOWN;
+    case SVM_EXIT_CPUID:
+    {
+        uint32_t out[4];
+        virt_cpuid(vcpu, (uint32_t)save_read(v, SAVE_RAX), (uint32_t)v->regs.rcx, out);
+        save_write(v, SAVE_RAX, out[0]);
+        v->regs.rbx = out[1];
+        v->regs.rcx = out[2];
+        v->regs.rdx = out[3];
+        svm_step(v, 2);
+        return HV_EXIT_NONE;
+    }
+    case SVM_EXIT_HLT:
+        svm_step(v, 1);
+        if (hv_irq_next(vcpu) >= 0 && (save_read(v, SAVE_RFLAGS) & RFLAGS_IF))
+        {
+            return HV_EXIT_NONE;
+        }
+        return HV_EXIT_HLT;
+    case SVM_EXIT_IOIO:
+        exit->io.port = (uint16_t)(info1 >> 16);
+        exit->io.size = (info1 & (1 << 4)) ? 1 : (info1 & (1 << 5)) ? 2 : 4;
+        exit->io.flags = (info1 & 1) ? 0 : HV_IO_WRITE;
+        exit->io.flags |= (info1 & (1 << 2)) ? HV_IO_STRING : 0;
+        exit->io.flags |= (info1 & (1 << 3)) ? HV_IO_REP : 0;
+        if ((exit->io.flags & HV_IO_WRITE) && !(exit->io.flags & HV_IO_STRING))
+        {
+            uint64_t rax = save_read(v, SAVE_RAX);
+            exit->io.data = rax & (exit->io.size == 4 ? 0xFFFFFFFFULL : (1ULL << (exit->io.size * 8)) - 1);
+        }
+        // EXITINFO2 is the address of the next instruction
+        v->step = (uint32_t)(info2 - save_read(v, SAVE_RIP));
+        return HV_EXIT_IO;
+    case SVM_EXIT_MSR:
+        return svm_msr(v, exit);
+    case SVM_EXIT_NPF:
+        exit->mmio.gpa = info2;
+        exit->mmio.gla = 0;
+        exit->mmio.access = (info1 & (1 << 1)) ? HV_ACCESS_WRITE : HV_ACCESS_READ;
+        exit->mmio.access |= (info1 & (1 << 4)) ? HV_ACCESS_EXEC : 0;
+        return HV_EXIT_MMIO;
+    case SVM_EXIT_INVALID:
+        exit->hw.code = code;
+        exit->hw.qualification = 0;
+        return HV_EXIT_FAIL_ENTRY;
+    default:
+        if (code >= SVM_EXIT_VMRUN && code <= SVM_EXIT_SKINIT)
+        {
+            // No nested virtualization
+            svm_inject_exception(v, VECTOR_UD, false, 0);
+            return HV_EXIT_NONE;
+        }
+        exit->hw.code = code;
+        exit->hw.qualification = info1;
+        return HV_EXIT_UNKNOWN;
+    }
+}
+
+static uint32_t svm_run(hv_vcpu_t *vcpu, hv_exit_t *exit)
+{
+    svm_vcpu_t *v = vcpu->backend;
+
+    if (vcpu->complete)
+    {
+        svm_complete(vcpu, v);
+        vcpu->complete = false;
+    }
+    svm_inject(vcpu, v);
+    bool flush = hv_tlb_flush_needed(vcpu, arch_get_current_cpu());
+    vmcb_write32(v, VMCB_TLB_CONTROL, flush ? TLB_CONTROL_FLUSH_ASID : 0);
+
+    // VMRUN loads RAX from the VMCB
+    svm_enter(&v->regs, v->vmcb, g_host_save[arch_get_current_cpu()]);
+    return svm_handle_exit(vcpu, v, exit);
+}
+
+static const hv_backend_t svm_backend = {
+    .name = "svm",
+    .cpu_enable = svm_cpu_enable,
+    .vm_init = svm_vm_init,
+    .vm_destroy = svm_vm_destroy,
+    .map = svm_map,
+    .unmap = svm_unmap,
+    .vcpu_init = svm_vcpu_init,
+    .vcpu_destroy = svm_vcpu_destroy,
+    .get_state = svm_get_state,
+    .set_state = svm_set_state,
+    .vcpu_load = virt_vcpu_load,
+    .vcpu_put = virt_vcpu_put,
+    .run = svm_run,
+};
+
+static uint64_t svm_filled_pages(uint32_t count)
+{
+    uint64_t first = pmm_alloc_pages(count);
+    if (first)
+    {
+        memset((void *)PHYS_TO_VIRT(first), 0xFF, (size_t)count * PAGE_SIZE);
+    }
+    return first;
+}
+
+int svm_init(void)
+{
+    uint32_t eax, ebx, ecx, edx;
+    cpuid(0x80000001, &eax, &ebx, &ecx, &edx);
+    if (!(ecx & CPUID80000001_ECX_SVM))
+    {
+        return -OR_ENODEV;
+    }
+    if (msr_read(MSR_VM_CR) & VM_CR_SVMDIS)
+    {
+        kinfo("SVM disabled by the firmware");
+        return -OR_ENODEV;
+    }
+    cpuid(0x8000000A, &eax, &ebx, &ecx, &edx);
+    if (!(edx & CPUID8000000A_EDX_NP))
+    {
+        kinfo("SVM present without nested paging, not used");
+        return -OR_ENOTSUP;
+    }
+    g_max_asid = ebx;
+
+    g_iopm = svm_filled_pages(IOPM_PAGES);
+    g_msrpm = svm_filled_pages(MSRPM_PAGES);
+    if (!g_iopm || !g_msrpm)
+    {
+        return -OR_ENOMEM;
+    }
+    return hv_register_backend(&svm_backend);
+}
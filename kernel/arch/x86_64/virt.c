@@ -0,0 +1,213 @@
+/*
+ * Orion Operating System - x86_64 Virtualization, Shared Parts
+ *
+ * EPT and NPT tables have the layout of ordinary 4-level page tables and
+ * differ only in their entry bits, which the backends pass in. Guests get
+ * no XSAVE: the FXSAVE image covers all the state they can touch.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <arch.h>
+#include "virt.h"
+
+// CPUID bits hidden from guests
+#define CPUID1_ECX_VMX (1U << 5)
+#define CPUID1_ECX_SMX (1U << 6)
+#define CPUID1_ECX_XSAVE (1U << 26)
+#define CPUID1_ECX_OSXSAVE (1U << 27)
+#define CPUID1_ECX_AVX (1U << 28)
+#define CPUID1_ECX_HYPERVISOR (1U << 31)
+#define CPUID7_EBX_AVX2 (1U << 5)
+#define CPUID7_EBX_AVX512 0xDC230000U // F, DQ, IFMA, PF, ER, CD, BW, VL
+#define CPUID80000001_ECX_SVM (1U << 2)
+
+// Hypervisor leaves
+#define CPUID_HV_BASE 0x40000000U
+#define CPUID_HV_SIGNATURE "OrionOS HV  "
+
+static void cpuid_count(uint32_t leaf, uint32_t subleaf, uint32_t out[4])
+{
+    __asm__ volatile("cpuid" : "=a"(out[0]), "=b"(out[1]), "=c"(out[2]), "=d"(out[3]) : "a"(leaf), "c"(subleaf));
+}
+
+void virt_cpuid(const hv_vcpu_t *vcpu, uint32_t leaf, uint32_t subleaf, uint32_t out[4])
+{
+    if (leaf == CPUID_HV_BASE)
+    {
+        const char *signature = CPUID_HV_SIGNATURE;
+        out[0] = CPUID_HV_BASE + 1;
+        memcpy(&out[1], signature, 12);
+        return;
+    }
+    if ((leaf & 0xF0000000U) == CPUID_HV_BASE || leaf == 0xD)
+    {
+        // No hypervisor features yet, and no XSAVE state
+        out[0] = out[1] = out[2] = out[3] = 0;
+        return;
+    }
+
+    cpuid_count(leaf, subleaf, out);
+    switch (leaf)
+    {
+    case 1:
+        out[1] = (out[1] & 0x00FFFFFFU) | (vcpu->index << 24); // Initial APIC ID
+        out[2] &= ~(CPUID1_ECX_VMX | CPUID1_ECX_SMX | CPUID1_ECX_XSAVE | CPUID1_ECX_OSXSAVE | CPUID1_ECX_AVX);
+        out[2] |= CPUID1_ECX_HYPERVISOR;
+        break;
+    case 7:
+        if (subleaf == 0)
+        {
+            out[1] &= ~(CPUID7_EBX_AVX2 | CPUID7_EBX_AVX512);
+            out[2] = 0; // AVX-512 extensions and protection keys
+        }
+        break;
+    case 0xA:
+        // No performance counters
+        out[0] = out[1] = out[2] = out[3] = 0;
+        break;
+    case 0x80000001:
+        out[2] &= ~CPUID80000001_ECX_SVM;
+        break;
+    default:
+        break;
+    }
+}
+
+void virt_reset_state(hv_vcpu_state_t *state)
+{
+    static const hv_segment_t data = {.base = 0, .limit = 0xFFFF, .selector = 0, .attributes = 0x93};
+
+    memset(state, 0, sizeof(*state));
+    state->regs.rip = 0xFFF0;
+    state->regs.rflags = 0x2;
+    state->regs.rdx = 0x600; // Family 6
+    state->cr0 = 0x60000010; // CD, NW, ET
+    state->cs = (hv_segment_t){.base = 0xFFFF0000, .limit = 0xFFFF, .selector = 0xF000, .attributes = 0x9B};
+    state->ds = state->es = state->fs = state->gs = state->ss = data;
+    state->tr = (hv_segment_t){.base = 0, .limit = 0xFFFF, .selector = 0, .attributes = 0x8B};
+    state->ldtr = (hv_segment_t){.base = 0, .limit = 0xFFFF, .selector = 0, .attributes = 0x82};
+    state->gdt.limit = 0xFFFF;
+    state->idt.limit = 0xFFFF;
+}
+
+// ========================================
+// SECOND-LEVEL PAGE TABLES
+// ========================================
+
+static uint64_t *table_of(uint64_t phys)
+{
+    return (uint64_t *)PHYS_TO_VIRT(phys & VIRT_PT_ADDR);
+}
+
+static uint64_t table_alloc(void)
+{
+    uint64_t phys = pmm_alloc_page();
+    if (phys)
+    {
+        memset(table_of(phys), 0, PAGE_SIZE);
+    }
+    return phys;
+}
+
+int virt_pt_map(uint64_t *root, uint64_t gpa, uint64_t entry, uint64_t table_flags)
+{
+    if (!*root && !(*root = table_alloc()))
+    {
+        return -OR_ENOMEM;
+    }
+
+    uint64_t *table = table_of(*root);
+    for (int shift = 39; shift > 12; shift -= 9)
+    {
+        uint64_t *slot = &table[(gpa >> shift) & 0x1FF];
+        if (!*slot)
+        {
+            uint64_t next = table_alloc();
+            if (!next)
+            {
+                return -OR_ENOMEM;
+            }
+            __atomic_store_n(slot, next | table_flags, __ATOMIC_RELEASE);
+        }
+        table = table_of(*slot);
+    }
+    __atomic_store_n(&table[(gpa >> 12) & 0x1FF], entry, __ATOMIC_RELEASE);
+    return OR_OK;
+}
+
+uint64_t virt_pt_unmap(uint64_t root, uint64_t gpa)
+{
+    if (!root)
+    {
+        return 0;
+    }
+    uint64_t *table = table_of(root);
+    for (int shift = 39; shift > 12; shift -= 9)
+    {
+        uint64_t next = table[(gpa >> shift) & 0x1FF];
+        if (!next)
+        {
+            return 0;
+        }
+        table = table_of(next);
+    }
+    return __atomic_exchange_n(&table[(gpa >> 12) & 0x1FF], 0, __ATOMIC_ACQ_REL);
+}
+
+static void table_free(uint64_t phys, int level)
+{
+    if (level > 1)
+    {
+        uint64_t *table = table_of(phys);
+        for (int i = 0; i < 512; i++)
+        {
+            if (table[i])
+            {
+                table_free(table[i], level - 1);
+            }
+        }
+    }
+    pmm_free_page(phys & VIRT_PT_ADDR);
+}
+
+void virt_pt_free(uint64_t root)
+{
+    if (root)
+    {
+        table_free(root, 4);
+    }
+}
+
+// ========================================
+// FPU
+// ========================================
+
+void virt_fpu_init(virt_fpu_t *fpu)
+{
+    memset(fpu, 0, sizeof(*fpu));
+    // Power-on FCW and MXCSR: every exception masked
+    *(uint16_t *)&fpu->guest[0] = 0x037F;
+    *(uint32_t *)&fpu->guest[24] = 0x1F80;
+}
+
+void virt_vcpu_load(hv_vcpu_t *vcpu)
+{
+    virt_fpu_t *fpu = vcpu->backend;
+    __asm__ volatile("fxsave64 %0" : "=m"(fpu->host));
+    __asm__ volatile("fxrstor64 %0" ::"m"(fpu->guest));
+}
+
+void virt_vcpu_put(hv_vcpu_t *vcpu)
+{
+    virt_fpu_t *fpu = vcpu->backend;
+    __asm__ volatile("fxsave64 %0" : "=m"(fpu->guest));
+    __asm__ volatile("fxrstor64 %0" ::"m"(fpu->host));
+}
@@ -0,0 +1,93 @@
+/*
+ * Orion Operating System - x86_64 Virtualization
+ *
+ * What the VMX (Intel) and SVM (AMD) backends of the hypervisor share:
+ * the CPUID a guest sees, the MSRs the kernel keeps for it, second-level
+ * page tables and the swap of FPU state around HV_OP_RUN. See
+ * core/virt/hypervisor.h for the interface they implement.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_X86_64_VIRT_H
+#define ORION_X86_64_VIRT_H
+
+#include <orion/types.h>
+#include <orion/hypervisor.h>
+
+// MSRs a guest kernel uses on every system call or context switch, kept
+// by the kernel rather than exiting to the VMM
+#define MSR_IA32_TSC 0x10
+#define MSR_IA32_SYSENTER_CS 0x174
+#define MSR_IA32_SYSENTER_ESP 0x175
+#define MSR_IA32_SYSENTER_EIP 0x176
+#define MSR_IA32_PAT 0x277
+#define MSR_FS_BASE 0xC0000100
+#define MSR_GS_BASE 0xC0000101
+#define MSR_KERNEL_GS_BASE 0xC0000102
+
+#define EFER_SCE (1ULL << 0)
+#define EFER_LME (1ULL << 8)
+#define EFER_LMA (1ULL << 10)
+#define EFER_SVME (1ULL << 12)
+
+#define CR0_PE (1ULL << 0)
+#define CR0_NE (1ULL << 5)
+#define CR0_PG (1ULL << 31)
+#define CR4_VMXE (1ULL << 13)
+
+#define RFLAGS_IF (1ULL << 9)
+
+// Power-on PAT of the processor
+#define PAT_DEFAULT 0x0007040600070406ULL
+
+// Second-level table entries point to the next level or the page here
+#define VIRT_PT_ADDR 0x000FFFFFFFFFF000ULL
+
+// Exceptions injected by the backends
+#define VECTOR_UD 6
+#define VECTOR_GP 13
+
+// Start of every backend's per-vCPU state: the FPU images swapped by
+// virt_vcpu_load and virt_vcpu_put (FXSAVE format)
+typedef struct virt_fpu
+{
+    uint8_t guest[512] __attribute__((aligned(16)));
+    uint8_t host[512] __attribute__((aligned(16)));
+} virt_fpu_t;
+
+// State of a vCPU at power-on: real mode at F000:FFF0
+void virt_reset_state(hv_vcpu_state_t *state);
+
+// Guest view of CPUID `leaf`/`subleaf` for `vcpu`, in eax, ebx, ecx, edx
+void virt_cpuid(const hv_vcpu_t *vcpu, uint32_t leaf, uint32_t subleaf, uint32_t out[4]);
+
+// Map `gpa` to the leaf `entry` in the tables rooted at the physical page
+// `*root`, allocated on first use; intermediate entries get `table_flags`
+int virt_pt_map(uint64_t *root, uint64_t gpa, uint64_t entry, uint64_t table_flags);
+
+// Clear the leaf entry of `gpa`, returning its previous value or 0
+uint64_t virt_pt_unmap(uint64_t root, uint64_t gpa);
+
+// Free the tables (not the pages they map)
+void virt_pt_free(uint64_t root);
+
+void virt_fpu_init(virt_fpu_t *fpu);
+void virt_vcpu_load(hv_vcpu_t *vcpu);
+void virt_vcpu_put(hv_vcpu_t *vcpu);
+
+// Backends; each registers itself when the processor supports it
+int vmx_init(void);
+int svm_init(void);
+
+// virt_asm.S: enter the guest with its general registers from `regs`
+// (RSP, RIP and RFLAGS excepted, and RAX for SVM) and store them back on
+// exit. vmx_enter returns 1 if VMLAUNCH failed, 0 after a VM exit
+int vmx_enter(hv_regs_t *regs);
+void svm_enter(hv_regs_t *regs, uint64_t vmcb_phys, uint64_t host_save_phys);
+
+#endif // ORION_X86_64_VIRT_H
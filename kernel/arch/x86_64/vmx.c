@@ -0,0 +1,1237 @@
+/*
+ * Orion Operating System - Intel VMX Backend
+ *
+ * Hypervisor backend for Intel VT-x with EPT and unrestricted guests, so
+ * that guests start in real mode like on bare metal. A vCPU's VMCS is
+ * only current on a CPU for one guest entry, with interrupts masked:
+ * VMPTRLD before, VMCLEAR after. That costs a VMCS flush per exit but lets
+ * the thread running the vCPU migrate freely between two entries, with
+ * no VMCS left behind on another CPU. VPIDs are not used, so entries and
+ * exits flush guest linear translations; EPT ones are flushed with
+ * INVEPT when hv_tlb_flush_needed says so.
+ *
+ * All port I/O and MSR accesses exit; CR0.NE and CR4.VMXE, which VMX
+ * needs set, are hidden from the guest behind read shadows.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/hypervisor.h>
+#include <arch.h>
+#include "virt.h"
+
+// Capability MSRs
+#define MSR_IA32_FEATURE_CONTROL 0x3A
+#define MSR_IA32_VMX_BASIC 0x480
+#define MSR_IA32_VMX_PINBASED_CTLS 0x481
+#define MSR_IA32_VMX_PROCBASED_CTLS 0x482
+#define MSR_IA32_VMX_EXIT_CTLS 0x483
+#define MSR_IA32_VMX_ENTRY_CTLS 0x484
+#define MSR_IA32_VMX_CR0_FIXED0 0x486
+#define MSR_IA32_VMX_CR0_FIXED1 0x487
+#define MSR_IA32_VMX_CR4_FIXED0 0x488
+#define MSR_IA32_VMX_CR4_FIXED1 0x489
+#define MSR_IA32_VMX_PROCBASED_CTLS2 0x48B
+#define MSR_IA32_VMX_EPT_VPID_CAP 0x48C
+#define MSR_IA32_VMX_TRUE_PINBASED_CTLS 0x48D
+#define MSR_IA32_VMX_TRUE_PROCBASED_CTLS 0x48E
+#define MSR_IA32_VMX_TRUE_EXIT_CTLS 0x48F
+#define MSR_IA32_VMX_TRUE_ENTRY_CTLS 0x490
+
+#define FEATURE_CONTROL_LOCKED (1ULL << 0)
+#define FEATURE_CONTROL_VMXON (1ULL << 2) // Outside SMX
+#define VMX_BASIC_TRUE_CTLS (1ULL << 55)
+
+#define EPT_CAP_WALK_4 (1ULL << 6)
+#define EPT_CAP_WB (1ULL << 14)
+#define EPT_CAP_INVEPT (1ULL << 20)
+#define EPT_CAP_INVEPT_SINGLE (1ULL << 25)
+#define EPT_CAP_INVEPT_ALL (1ULL << 26)
+
+// Controls
+#define PIN_EXTERNAL_INTERRUPT (1U << 0)
+#define PIN_NMI (1U << 3)
+#define PROC_INTERRUPT_WINDOW (1U << 2)
+#define PROC_HLT (1U << 7)
+#define PROC_UNCONDITIONAL_IO (1U << 24)
+#define PROC_SECONDARY (1U << 31)
+#define PROC2_EPT (1U << 1)
+#define PROC2_UNRESTRICTED (1U << 7)
+#define EXIT_HOST_64 (1U << 9)
+#define EXIT_SAVE_EFER (1U << 20)
+#define EXIT_LOAD_EFER (1U << 21)
+#define ENTRY_IA32E (1U << 9)
+#define ENTRY_LOAD_EFER (1U << 15)
+
+// VMCS fields
+#define VMCS_GUEST_ES_SELECTOR 0x0800 // Then CS, SS, DS, FS, GS, LDTR, TR by 2
+#define VMCS_HOST_ES_SELECTOR 0x0C00
+#define VMCS_HOST_CS_SELECTOR 0x0C02
+#define VMCS_HOST_SS_SELECTOR 0x0C04
+#define VMCS_HOST_DS_SELECTOR 0x0C06
+#define VMCS_HOST_FS_SELECTOR 0x0C08
+#define VMCS_HOST_GS_SELECTOR 0x0C0A
+#define VMCS_HOST_TR_SELECTOR 0x0C0C
+#define VMCS_EXIT_MSR_STORE_ADDR 0x2006
+#define VMCS_EXIT_MSR_LOAD_ADDR 0x2008
+#define VMCS_ENTRY_MSR_LOAD_ADDR 0x200A
+#define VMCS_EPT_POINTER 0x201A
+#define VMCS_GUEST_PHYSICAL_ADDRESS 0x2400
+#define VMCS_LINK_POINTER 0x2800
+#define VMCS_GUEST_EFER 0x2806
+#define VMCS_HOST_EFER 0x2C02
+#define VMCS_PIN_CONTROLS 0x4000
+#define VMCS_PROC_CONTROLS 0x4002
+#define VMCS_EXCEPTION_BITMAP 0x4004
+#define VMCS_EXIT_CONTROLS 0x400C
+#define VMCS_EXIT_MSR_STORE_COUNT 0x400E
+#define VMCS_EXIT_MSR_LOAD_COUNT 0x4010
+#define VMCS_ENTRY_CONTROLS 0x4012
+#define VMCS_ENTRY_MSR_LOAD_COUNT 0x4014
+#define VMCS_ENTRY_INTR_INFO 0x4016
+#define VMCS_ENTRY_EXCEPTION_ERROR 0x4018
+#define VMCS_ENTRY_INSTRUCTION_LEN 0x401A
+#define VMCS_PROC2_CONTROLS 0x401E
+#define VMCS_INSTRUCTION_ERROR 0x4400
+#define VMCS_EXIT_REASON 0x4402
+#define VMCS_EXIT_INTR_INFO 0x4404
+#define VMCS_IDT_VECTORING_INFO 0x4408
+#define VMCS_IDT_VECTORING_ERROR 0x440A
+#define VMCS_EXIT_INSTRUCTION_LEN 0x440C
+#define VMCS_GUEST_ES_LIMIT 0x4800 // Then as the selectors, GDTR and IDTR
+#define VMCS_GUEST_GDTR_LIMIT 0x4810
+#define VMCS_GUEST_IDTR_LIMIT 0x4812
+#define VMCS_GUEST_ES_AR 0x4814 // Then as the selectors
+#define VMCS_GUEST_INTERRUPTIBILITY 0x4824
+#define VMCS_GUEST_ACTIVITY 0x4826
+#define VMCS_GUEST_SYSENTER_CS 0x482A
+#define VMCS_HOST_SYSENTER_CS 0x4C00
+#define VMCS_CR0_MASK 0x6000
+#define VMCS_CR4_MASK 0x6002
+#define VMCS_CR0_SHADOW 0x6004
+#define VMCS_CR4_SHADOW 0x6006
+#define VMCS_EXIT_QUALIFICATION 0x6400
+#define VMCS_GUEST_LINEAR_ADDRESS 0x640A
+#define VMCS_GUEST_CR0 0x6800
+#define VMCS_GUEST_CR3 0x6802
+#define VMCS_GUEST_CR4 0x6804
+#define VMCS_GUEST_ES_BASE 0x6806 // Then as the selectors, GDTR and IDTR
+#define VMCS_GUEST_GDTR_BASE 0x6816
+#define VMCS_GUEST_IDTR_BASE 0x6818
+#define VMCS_GUEST_DR7 0x681A
+#define VMCS_GUEST_RSP 0x681C
+#define VMCS_GUEST_RIP 0x681E
+#define VMCS_GUEST_RFLAGS 0x6820
+#define VMCS_GUEST_PENDING_DEBUG 0x6822
+#define VMCS_GUEST_SYSENTER_ESP 0x6824
+#define VMCS_GUEST_SYSENTER_EIP 0x6826
+#define VMCS_HOST_CR0 0x6C00
+#define VMCS_HOST_CR3 0x6C02
+#define VMCS_HOST_CR4 0x6C04
+#define VMCS_HOST_FS_BASE 0x6C06
+#define VMCS_HOST_GS_BASE 0x6C08
+#define VMCS_HOST_TR_BASE 0x6C0A
+#define VMCS_HOST_GDTR_BASE 0x6C0C
+#define VMCS_HOST_IDTR_BASE 0x6C0E
+#define VMCS_HOST_SYSENTER_ESP 0x6C10
+#define VMCS_HOST_SYSENTER_EIP 0x6C12
+
+// Segment order of the guest segment fields
+enum
+{
+    SEG_ES,
+    SEG_CS,
+    SEG_SS,
+    SEG_DS,
+    SEG_FS,
+    SEG_GS,
+    SEG_LDTR,
+    SEG_TR,
+};
+
+#define AR_UNUSABLE (1U << 16)
+
+// Interruption information (entry, exit and IDT vectoring)
+#define INTR_INFO_VALID (1U << 31)
+#define INTR_INFO_ERROR (1U << 11)
+#define INTR_TYPE_EXTERNAL (0U << 8)
+#define INTR_TYPE_NMI (2U << 8)
+#define INTR_TYPE_EXCEPTION (3U << 8)
+#define INTR_TYPE_MASK (7U << 8)
+
+#define INTERRUPTIBILITY_STI (1U << 0)
+#define INTERRUPTIBILITY_MOV_SS (1U << 1)
+
+// Basic exit reasons
+#define EXIT_EXCEPTION_NMI 0
+#define EXIT_EXTERNAL_INTERRUPT 1
+#define EXIT_TRIPLE_FAULT 2
+#define EXIT_INTERRUPT_WINDOW 7
+#define EXIT_CPUID 10
+#define EXIT_HLT 12
+#define EXIT_VMCALL 18
+#define EXIT_VMXON 27
+#define EXIT_CR_ACCESS 28
+#define EXIT_IO 30
+#define EXIT_RDMSR 31
+#define EXIT_WRMSR 32
+#define EXIT_EPT_VIOLATION 48
+#define EXIT_INVEPT 50
+#define EXIT_INVVPID 53
+#define EXIT_XSETBV 55
+#define EXIT_ENTRY_FAILURE (1U << 31)
+
+// EPT entries
+#define EPT_READ (1ULL << 0)
+#define EPT_WRITE (1ULL << 1)
+#define EPT_EXEC (1ULL << 2)
+#define EPT_TABLE (EPT_READ | EPT_WRITE | EPT_EXEC)
+#define EPT_MEMTYPE_WB (6ULL << 3)
+#define EPT_IGNORE_PAT (1ULL << 6)
+#define EPTP_WALK_4 (3ULL << 3)
+
+#define INVEPT_SINGLE 1
+#define INVEPT_ALL 2
+
+// Guest MSRs switched through the VMCS MSR areas
+static const uint32_t g_switched_msrs[] = {MSR_STAR, MSR_LSTAR, MSR_CSTAR, MSR_SFMASK, MSR_KERNEL_GS_BASE};
+#define SWITCHED_MSRS (sizeof(g_switched_msrs) / sizeof(g_switched_msrs[0]))
+
+typedef struct vmx_msr_entry
+{
+    uint32_t index;
+    uint32_t reserved;
+    uint64_t value;
+} vmx_msr_entry_t;
+
+typedef struct vmx_vcpu
+{
+    virt_fpu_t fpu; // First, see virt.h
+    uint64_t vmcs;  // Physical pages
+    uint64_t msrs;  // Guest area, then the host one at MSR_HOST_OFFSET
+    hv_regs_t regs; // RSP, RIP and RFLAGS live in the VMCS
+    uint64_t cr2;
+    uint64_t pat;
+    uint32_t step;        // Length of the instruction awaiting completion
+    uint32_t event_info;  // Event to inject on the next entry
+    uint32_t event_error;
+    uint32_t event_length;
+} vmx_vcpu_t;
+
+#define MSR_HOST_OFFSET 2048
+
+typedef struct vmx_vm
+{
+    uint64_t ept_root;
+} vmx_vm_t;
+
+static uint32_t g_revision;
+static uint32_t g_pin, g_proc, g_proc2, g_exit, g_entry;
+static uint64_t g_cr0_fixed0, g_cr0_fixed1, g_cr4_fixed0, g_cr4_fixed1;
+static uint32_t g_invept_type;
+static uint64_t g_vmxon[MAX_CPUS];
+
+// ========================================
+// INSTRUCTIONS
+// ========================================
+
+static inline int vmx_op_result(uint8_t failed)
+{
+    return failed ? -OR_EIO : OR_OK;
+}
+
+static inline int vmxon(uint64_t phys)
+{
+    uint8_t failed;
+    __asm__ volatile("vmxon %1; setna %0" : "=qm"(failed) : "m"(phys) : "cc", "memory");
+    return vmx_op_result(failed);
+}
+
+static inline int vmclear(uint64_t phys)
+{
+    uint8_t failed;
+    __asm__ volatile("vmclear %1; setna %0" : "=qm"(failed) : "m"(phys) : "cc", "memory");
+    return vmx_op_result(failed);
+}
+
+static inline int vmptrld(uint64_t phys)
+{
+    uint8_t failed;
+    __asm__ volatile("vmptrld %1; setna %0" : "=qm"(failed) : "m"(phys) : "cc", "memory");
+    return vmx_op_result(failed);
+}
+
+static inline uint64_t vmread(uint64_t field)
+{
+    uint64_t value;
+    __asm__ volatile("vmread %1, %0" : "=rm"(value) : "r"(field) : "cc");
+    return value;
+}
+
+static inline void vmwrite(uint64_t field, uint64_t value)
+{
+    __asm__ volatile("vmwrite %1, %0" ::"r"(field), "rm"(value) : "cc");
+}
+
+static inline void invept(uint32_t type, uint64_t eptp)
+{
+    struct
+    {
+        uint64_t eptp;
+        uint64_t reserved;
+    } descriptor = {eptp, 0};
+    __asm__ volatile("invept %0, %1" ::"m"(descriptor), "r"((uint64_t)type) : "cc", "memory");
+}
+
+static inline uint64_t read_cr2(void)
+{
+    uint64_t value;
+    __asm__ volatile("mov %%cr2, %0" : "=r"(value));
+    return value;
+}
+
+static inline void write_cr2(uint64_t value)
+{
+    __asm__ volatile("mov %0, %%cr2" ::"r"(value));
+}
+
+// ========================================
+// SET UP
+// ========================================
+
+// Controls with the `wanted` bits set, those the processor requires set
+// and no others; false if it does not allow one of the wanted bits
+static bool vmx_controls(uint32_t msr, uint32_t wanted, uint32_t *controls)
+{
+    uint64_t allowed = msr_read(msr);
+    uint32_t value = (wanted | (uint32_t)allowed) & (uint32_t)(allowed >> 32);
+    *controls = value;
+    return (value & wanted) == wanted;
+}
+
+// VMXON is allowed, enabling it if the firmware left the MSR unlocked
+static bool vmx_feature_control(void)
+{
+    uint64_t control = msr_read(MSR_IA32_FEATURE_CONTROL);
+    if (!(control & FEATURE_CONTROL_LOCKED))
+    {
+        control |= FEATURE_CONTROL_LOCKED | FEATURE_CONTROL_VMXON;
+        msr_write(MSR_IA32_FEATURE_CONTROL, control);
+    }
+    return (control & FEATURE_CONTROL_VMXON) != 0;
+}
+
+typedef struct
+{
+    uint16_t limit;
+    uint64_t base;
+} PACKED vmx_dtr_t;
+
+static uint64_t descriptor_base(uint64_t gdt, uint16_t selector)
+{
+    const uint64_t *entry = (const uint64_t *)(gdt + (selector & ~7U));
+    uint64_t base = ((entry[0] >> 16) & 0xFFFFFF) | (((entry[0] >> 56) & 0xFF) << 24);
+    return base | (entry[1] << 32);
+}
+
+// VM exits load a task register, which the kernel does not otherwise
+// use: give a CPU without one a copy of its GDT with an idle TSS
+static int vmx_host_tr(void)
+{
+    uint16_t tr;
+    __asm__ volatile("str %0" : "=r"(tr));
+    if (tr)
+    {
+        return OR_OK;
+    }
+
+    vmx_dtr_t gdtr;
+    __asm__ volatile("sgdt %0" : "=m"(gdtr));
+    uint32_t used = ((uint32_t)gdtr.limit + 1 + 7) & ~7U;
+    if (used + 16 > PAGE_SIZE / 2)
+    {
+        return -OR_ENOSPC;
+    }
+    uint64_t page = pmm_alloc_page();
+    if (!page)
+    {
+        return -OR_ENOMEM;
+    }
+
+    // GDT in the first half of the page, TSS in the second
+    uint8_t *gdt = (uint8_t *)PHYS_TO_VIRT(page);
+    memset(gdt, 0, PAGE_SIZE);
+    memcpy(gdt, (const void *)gdtr.base, (size_t)gdtr.limit + 1);
+    uint64_t tss = (uint64_t)gdt + PAGE_SIZE / 2;
+    *(uint16_t *)(tss + 102) = 104; // No I/O permission bitmap
+
+    uint64_t limit = 103;
+    uint64_t *descriptor = (uint64_t *)(gdt + used);
+    descriptor[0] = (limit & 0xFFFF) | ((tss & 0xFFFFFF) << 16) | (0x89ULL << 40) | (((tss >> 24) & 0xFF) << 56);
+    descriptor[1] = tss >> 32;
+
+    gdtr.base = (uint64_t)gdt;
+    gdtr.limit = (uint16_t)(used + 16 - 1);
+    uint16_t selector = (uint16_t)used;
+    __asm__ volatile("lgdt %0; ltr %1" ::"m"(gdtr), "r"(selector) : "memory");
+    return OR_OK;
+}
+
+static int vmx_cpu_enable(void)
+{
+    uint32_t cpu = arch_get_current_cpu();
+    if (!vmx_feature_control())
+    {
+        return -OR_ENODEV;
+    }
+    int result = vmx_host_tr();
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    uint64_t region = g_vmxon[cpu] ? g_vmxon[cpu] : pmm_alloc_page();
+    if (!region)
+    {
+        return -OR_ENOMEM;
+    }
+    memset((void *)PHYS_TO_VIRT(region), 0, PAGE_SIZE);
+    *(uint32_t *)PHYS_TO_VIRT(region) = g_revision;
+
+    write_cr0((read_cr0() | g_cr0_fixed0) & g_cr0_fixed1);
+    write_cr4((read_cr4() | CR4_VMXE | g_cr4_fixed0) & g_cr4_fixed1);
+    if (vmxon(region) != OR_OK)
+    {
+        write_cr4(read_cr4() & ~CR4_VMXE);
+        pmm_free_page(region);
+        return -OR_EIO;
+    }
+    g_vmxon[cpu] = region;
+    return OR_OK;
+}
+
+static uint64_t vmx_eptp(const hv_vm_t *vm)
+{
+    const vmx_vm_t *state = vm->backend;
+    return state->ept_root | EPTP_WALK_4 | 6; // Write-back paging structures
+}
+
+static int vmx_vm_init(hv_vm_t *vm)
+{
+    vmx_vm_t *state = kmalloc(sizeof(*state));
+    if (!state)
+    {
+        return -OR_ENOMEM;
+    }
+    // The root exists from the start: it is in every VMCS of the VM
+    state->ept_root = pmm_alloc_page();
+    if (!state->ept_root)
+    {
+        kfree(state);
+        return -OR_ENOMEM;
+    }
+    memset((void *)PHYS_TO_VIRT(state->ept_root), 0, PAGE_SIZE);
+    vm->backend = state;
+    return OR_OK;
+}
+
+static void vmx_vm_destroy(hv_vm_t *vm)
+{
+    vmx_vm_t *state = vm->backend;
+    if (state)
+    {
+        virt_pt_free(state->ept_root);
+        kfree(state);
+        vm->backend = NULL;
+    }
+}
+
+static int vmx_map(hv_vm_t *vm, uint64_t gpa, uint64_t hpa, uint32_t flags)
+{
+    vmx_vm_t *state = vm->backend;
+    uint64_t entry = hpa | EPT_READ | EPT_MEMTYPE_WB | EPT_IGNORE_PAT;
+    entry |= (flags & HV_MEM_WRITE) ? EPT_WRITE : 0;
+    entry |= (flags & HV_MEM_EXEC) ? EPT_EXEC : 0;
+    return virt_pt_map(&state->ept_root, gpa, entry, EPT_TABLE);
+}
+
+static uint64_t vmx_unmap(hv_vm_t *vm, uint64_t gpa)
+{
+    vmx_vm_t *state = vm->backend;
+    return virt_pt_unmap(state->ept_root, gpa) & VIRT_PT_ADDR;
+}
+
+// ========================================
+// GUEST STATE
+// ========================================
+
+static vmx_msr_entry_t *vmx_guest_msrs(vmx_vcpu_t *v)
+{
+    return (vmx_msr_entry_t *)PHYS_TO_VIRT(v->msrs);
+}
+
+static uint64_t *vmx_switched_msr(vmx_vcpu_t *v, uint32_t index)
+{
+    vmx_msr_entry_t *msrs = vmx_guest_msrs(v);
+    for (size_t i = 0; i < SWITCHED_MSRS; i++)
+    {
+        if (msrs[i].index == index)
+        {
+            return &msrs[i].value;
+        }
+    }
+    return NULL;
+}
+
+static void vmx_write_segment(int segment, const hv_segment_t *value)
+{
+    uint32_t ar = value->attributes;
+    if (!(ar & 0x80))
+    {
+        ar |= AR_UNUSABLE;
+    }
+    vmwrite(VMCS_GUEST_ES_SELECTOR + 2 * segment, value->selector);
+    vmwrite(VMCS_GUEST_ES_LIMIT + 2 * segment, value->limit);
+    vmwrite(VMCS_GUEST_ES_AR + 2 * segment, ar);
+    vmwrite(VMCS_GUEST_ES_BASE + 2 * segment, value->base);
+}
+
+static void vmx_read_segment(int segment, hv_segment_t *value)
+{
+    uint32_t ar = (uint32_t)vmread(VMCS_GUEST_ES_AR + 2 * segment);
+    value->selector = (uint16_t)vmread(VMCS_GUEST_ES_SELECTOR + 2 * segment);
+    value->limit = (uint32_t)vmread(VMCS_GUEST_ES_LIMIT + 2 * segment);
+    value->base = vmread(VMCS_GUEST_ES_BASE + 2 * segment);
+    value->attributes = (uint16_t)(ar & 0xF0FF);
+    if (ar & AR_UNUSABLE)
+    {
+        value->attributes &= ~0x80;
+    }
+}
+
+// EFER.LMA follows LME and CR0.PG, and the guest runs in IA-32e mode with it
+static void vmx_set_efer(uint64_t efer, uint64_t cr0)
+{
+    bool lma = (efer & EFER_LME) && (cr0 & CR0_PG);
+    efer = lma ? (efer | EFER_LMA) : (efer & ~EFER_LMA);
+    vmwrite(VMCS_GUEST_EFER, efer);
+    uint64_t entry = vmread(VMCS_ENTRY_CONTROLS);
+    vmwrite(VMCS_ENTRY_CONTROLS, lma ? (entry | ENTRY_IA32E) : (entry & ~(uint64_t)ENTRY_IA32E));
+}
+
+// Unrestricted guests may clear PE and PG despite the fixed bits
+static void vmx_set_cr0(uint64_t cr0)
+{
+    vmwrite(VMCS_CR0_SHADOW, cr0);
+    vmwrite(VMCS_GUEST_CR0, (cr0 | (g_cr0_fixed0 & ~(CR0_PE | CR0_PG))) & g_cr0_fixed1);
+}
+
+static void vmx_set_cr4(uint64_t cr4)
+{
+    vmwrite(VMCS_CR4_SHADOW, cr4);
+    vmwrite(VMCS_GUEST_CR4, (cr4 | g_cr4_fixed0) & g_cr4_fixed1);
+}
+
+static uint64_t vmx_guest_cr0(void)
+{
+    uint64_t mask = vmread(VMCS_CR0_MASK);
+    return (vmread(VMCS_GUEST_CR0) & ~mask) | (vmread(VMCS_CR0_SHADOW) & mask);
+}
+
+static uint64_t vmx_guest_cr4(void)
+{
+    uint64_t mask = vmread(VMCS_CR4_MASK);
+    return (vmread(VMCS_GUEST_CR4) & ~mask) | (vmread(VMCS_CR4_SHADOW) & mask);
+}
+
+// Caller made the VMCS current
+static void vmx_write_state(vmx_vcpu_t *v, const hv_vcpu_state_t *state)
+{
+    v->regs = state->regs;
+    v->cr2 = state->cr2;
+    vmwrite(VMCS_GUEST_RSP, state->regs.rsp);
+    vmwrite(VMCS_GUEST_RIP, state->regs.rip);
+    vmwrite(VMCS_GUEST_RFLAGS, state->regs.rflags | 0x2);
+
+    vmx_set_cr0(state->cr0);
+    vmx_set_cr4(state->cr4);
+    vmwrite(VMCS_GUEST_CR3, state->cr3);
+    vmx_set_efer(state->efer, state->cr0);
+
+    vmx_write_segment(SEG_ES, &state->es);
+    vmx_write_segment(SEG_CS, &state->cs);
+    vmx_write_segment(SEG_SS, &state->ss);
+    vmx_write_segment(SEG_DS, &state->ds);
+    vmx_write_segment(SEG_FS, &state->fs);
+    vmx_write_segment(SEG_GS, &state->gs);
+    vmx_write_segment(SEG_LDTR, &state->ldtr);
+    vmx_write_segment(SEG_TR, &state->tr);
+    vmwrite(VMCS_GUEST_GDTR_BASE, state->gdt.base);
+    vmwrite(VMCS_GUEST_GDTR_LIMIT, state->gdt.limit);
+    vmwrite(VMCS_GUEST_IDTR_BASE, state->idt.base);
+    vmwrite(VMCS_GUEST_IDTR_LIMIT, state->idt.limit);
+
+    *vmx_switched_msr(v, MSR_STAR) = state->star;
+    *vmx_switched_msr(v, MSR_LSTAR) = state->lstar;
+    *vmx_switched_msr(v, MSR_CSTAR) = state->cstar;
+    *vmx_switched_msr(v, MSR_SFMASK) = state->sfmask;
+    *vmx_switched_msr(v, MSR_KERNEL_GS_BASE) = state->kernel_gs_base;
+    vmwrite(VMCS_GUEST_SYSENTER_CS, state->sysenter_cs);
+    vmwrite(VMCS_GUEST_SYSENTER_ESP, state->sysenter_esp);
+    vmwrite(VMCS_GUEST_SYSENTER_EIP, state->sysenter_eip);
+
+    // Whatever the guest was doing is over
+    vmwrite(VMCS_GUEST_INTERRUPTIBILITY, 0);
+    vmwrite(VMCS_GUEST_ACTIVITY, 0);
+    vmwrite(VMCS_GUEST_PENDING_DEBUG, 0);
+    v->event_info = 0;
+    v->step = 0;
+}
+
+static void vmx_read_state(vmx_vcpu_t *v, hv_vcpu_state_t *state)
+{
+    state->regs = v->regs;
+    state->regs.rsp = vmread(VMCS_GUEST_RSP);
+    state->regs.rip = vmread(VMCS_GUEST_RIP);
+    state->regs.rflags = vmread(VMCS_GUEST_RFLAGS);
+    state->cr0 = vmx_guest_cr0();
+    state->cr2 = v->cr2;
+    state->cr3 = vmread(VMCS_GUEST_CR3);
+    state->cr4 = vmx_guest_cr4();
+    state->efer = vmread(VMCS_GUEST_EFER);
+
+    vmx_read_segment(SEG_ES, &state->es);
+    vmx_read_segment(SEG_CS, &state->cs);
+    vmx_read_segment(SEG_SS, &state->ss);
+    vmx_read_segment(SEG_DS, &state->ds);
+    vmx_read_segment(SEG_FS, &state->fs);
+    vmx_read_segment(SEG_GS, &state->gs);
+    vmx_read_segment(SEG_LDTR, &state->ldtr);
+    vmx_read_segment(SEG_TR, &state->tr);
+    state->gdt.base = vmread(VMCS_GUEST_GDTR_BASE);
+    state->gdt.limit = (uint16_t)vmread(VMCS_GUEST_GDTR_LIMIT);
+    state->idt.base = vmread(VMCS_GUEST_IDTR_BASE);
+    state->idt.limit = (uint16_t)vmread(VMCS_GUEST_IDTR_LIMIT);
+
+    state->star = *vmx_switched_msr(v, MSR_STAR);
+    state->lstar = *vmx_switched_msr(v, MSR_LSTAR);
+    state->cstar = *vmx_switched_msr(v, MSR_CSTAR);
+    state->sfmask = *vmx_switched_msr(v, MSR_SFMASK);
+    state->kernel_gs_base = *vmx_switched_msr(v, MSR_KERNEL_GS_BASE);
+    state->sysenter_cs = vmread(VMCS_GUEST_SYSENTER_CS);
+    state->sysenter_esp = vmread(VMCS_GUEST_SYSENTER_ESP);
+    state->sysenter_eip = vmread(VMCS_GUEST_SYSENTER_EIP);
+}
+
+static void vmx_get_state(hv_vcpu_t *vcpu, hv_vcpu_state_t *state)
+{
+    vmx_vcpu_t *v = vcpu->backend;
+    vmptrld(v->vmcs);
+    vmx_read_state(v, state);
+    vmclear(v->vmcs);
+}
+
+static int vmx_set_state(hv_vcpu_t *vcpu, const hv_vcpu_state_t *state)
+{
+    vmx_vcpu_t *v = vcpu->backend;
+    if (vmptrld(v->vmcs) != OR_OK)
+    {
+        return -OR_EIO;
+    }
+    vmx_write_state(v, state);
+    vmclear(v->vmcs);
+    return OR_OK;
+}
+
+// ========================================
+// VCPUS
+// ========================================
+
+static int vmx_vcpu_init(hv_vcpu_t *vcpu)
+{
+    uint64_t page = pmm_alloc_page();
+    if (!page)
+    {
+        return -OR_ENOMEM;
+    }
+    vmx_vcpu_t *v = (vmx_vcpu_t *)PHYS_TO_VIRT(page);
+    memset(v, 0, sizeof(*v));
+    virt_fpu_init(&v->fpu);
+    v->pat = PAT_DEFAULT;
+    v->vmcs = pmm_alloc_page();
+    v->msrs = pmm_alloc_page();
+    vcpu->backend = v;
+    if (!v->vmcs || !v->msrs)
+    {
+        return -OR_ENOMEM;
+    }
+
+    memset((void *)PHYS_TO_VIRT(v->vmcs), 0, PAGE_SIZE);
+    *(uint32_t *)PHYS_TO_VIRT(v->vmcs) = g_revision;
+    memset((void *)PHYS_TO_VIRT(v->msrs), 0, PAGE_SIZE);
+    vmx_msr_entry_t *guest = vmx_guest_msrs(v);
+    vmx_msr_entry_t *host = (vmx_msr_entry_t *)PHYS_TO_VIRT(v->msrs + MSR_HOST_OFFSET);
+    for (size_t i = 0; i < SWITCHED_MSRS; i++)
+    {
+        guest[i].index = host[i].index = g_switched_msrs[i];
+    }
+
+    if (vmclear(v->vmcs) != OR_OK || vmptrld(v->vmcs) != OR_OK)
+    {
+        return -OR_EIO;
+    }
+    vmwrite(VMCS_PIN_CONTROLS, g_pin);
+    vmwrite(VMCS_PROC_CONTROLS, g_proc);
+    vmwrite(VMCS_PROC2_CONTROLS, g_proc2);
+    vmwrite(VMCS_EXIT_CONTROLS, g_exit);
+    vmwrite(VMCS_ENTRY_CONTROLS, g_entry);
+    vmwrite(VMCS_EXCEPTION_BITMAP, 0);
+    vmwrite(VMCS_CR0_MASK, CR0_NE);
+    vmwrite(VMCS_CR4_MASK, CR4_VMXE);
+    vmwrite(VMCS_EPT_POINTER, vmx_eptp(vcpu->vm));
+    vmwrite(VMCS_LINK_POINTER, ~0ULL);
+    vmwrite(VMCS_GUEST_DR7, 0x400);
+    vmwrite(VMCS_ENTRY_MSR_LOAD_ADDR, v->msrs);
+    vmwrite(VMCS_EXIT_MSR_STORE_ADDR, v->msrs);
+    vmwrite(VMCS_EXIT_MSR_LOAD_ADDR, v->msrs + MSR_HOST_OFFSET);
+    vmwrite(VMCS_ENTRY_MSR_LOAD_COUNT, SWITCHED_MSRS);
+    vmwrite(VMCS_EXIT_MSR_STORE_COUNT, SWITCHED_MSRS);
+    vmwrite(VMCS_EXIT_MSR_LOAD_COUNT, SWITCHED_MSRS);
+
+    hv_vcpu_state_t reset;
+    virt_reset_state(&reset);
+    vmx_write_state(v, &reset);
+    vmclear(v->vmcs);
+    return OR_OK;
+}
+
+static void vmx_vcpu_destroy(hv_vcpu_t *vcpu)
+{
+    vmx_vcpu_t *v = vcpu->backend;
+    if (!v)
+    {
+        return;
+    }
+    if (v->vmcs)
+    {
+        pmm_free_page(v->vmcs);
+    }
+    if (v->msrs)
+    {
+        pmm_free_page(v->msrs);
+    }
+    pmm_free_page(VIRT_TO_PHYS(v));
+    vcpu->backend = NULL;
+}
+
+// ========================================
+// RUNNING
+// ========================================
+
+static uint16_t selector_of(int which)
+{
+    uint16_t selector = 0;
+    switch (which)
+    {
+    case SEG_CS:
+        __asm__ volatile("mov %%cs, %0" : "=r"(selector));
+        break;
+    case SEG_SS:
+        __asm__ volatile("mov %%ss, %0" : "=r"(selector));
+        break;
+    case SEG_DS:
+        __asm__ volatile("mov %%ds, %0" : "=r"(selector));
+        break;
+    case SEG_ES:
+        __asm__ volatile("mov %%es, %0" : "=r"(selector));
+        break;
+    case SEG_FS:
+        __asm__ volatile("mov %%fs, %0" : "=r"(selector));
+        break;
+    case SEG_GS:
+        __asm__ volatile("mov %%gs, %0" : "=r"(selector));
+        break;
+    case SEG_TR:
+        __asm__ volatile("str %0" : "=r"(selector));
+        break;
+    default:
+        break;
+    }
+    // Host selectors must have RPL and TI clear
+    return selector & ~7U;
+}
+
+// The host state of this CPU and thread, restored by the next VM exit
+static void vmx_write_host_state(vmx_vcpu_t *v)
+{
+    vmx_dtr_t gdtr, idtr;
+    __asm__ volatile("sgdt %0" : "=m"(gdtr));
+    __asm__ volatile("sidt %0" : "=m"(idtr));
+    uint16_t tr = selector_of(SEG_TR);
+
+    vmwrite(VMCS_HOST_CR0, read_cr0());
+    vmwrite(VMCS_HOST_CR3, read_cr3());
+    vmwrite(VMCS_HOST_CR4, read_cr4());
+    vmwrite(VMCS_HOST_CS_SELECTOR, selector_of(SEG_CS));
+    vmwrite(VMCS_HOST_SS_SELECTOR, selector_of(SEG_SS));
+    vmwrite(VMCS_HOST_DS_SELECTOR, selector_of(SEG_DS));
+    vmwrite(VMCS_HOST_ES_SELECTOR, selector_of(SEG_ES));
+    vmwrite(VMCS_HOST_FS_SELECTOR, selector_of(SEG_FS));
+    vmwrite(VMCS_HOST_GS_SELECTOR, selector_of(SEG_GS));
+    vmwrite(VMCS_HOST_TR_SELECTOR, tr);
+    vmwrite(VMCS_HOST_FS_BASE, msr_read(MSR_FS_BASE));
+    vmwrite(VMCS_HOST_GS_BASE, msr_read(MSR_GS_BASE));
+    vmwrite(VMCS_HOST_TR_BASE, descriptor_base(gdtr.base, tr));
+    vmwrite(VMCS_HOST_GDTR_BASE, gdtr.base);
+    vmwrite(VMCS_HOST_IDTR_BASE, idtr.base);
+    vmwrite(VMCS_HOST_SYSENTER_CS, msr_read(MSR_IA32_SYSENTER_CS));
+    vmwrite(VMCS_HOST_SYSENTER_ESP, msr_read(MSR_IA32_SYSENTER_ESP));
+    vmwrite(VMCS_HOST_SYSENTER_EIP, msr_read(MSR_IA32_SYSENTER_EIP));
+    vmwrite(VMCS_HOST_EFER, msr_read(MSR_EFER));
+
+    vmx_msr_entry_t *host = (vmx_msr_entry_t *)PHYS_TO_VIRT(v->msrs + MSR_HOST_OFFSET);
+    for (size_t i = 0; i < SWITCHED_MSRS; i++)
+    {
+        host[i].value = msr_read(host[i].index);
+    }
+}
+
+static void vmx_queue_exception(vmx_vcpu_t *v, uint32_t vector, bool has_error, uint32_t error)
+{
+    v->event_info = vector | INTR_TYPE_EXCEPTION | INTR_INFO_VALID | (has_error ? INTR_INFO_ERROR : 0);
+    v->event_error = error;
+    v->event_length = 0;
+}
+
+// Step over the instruction that exited
+static void vmx_step(uint32_t length)
+{
+    vmwrite(VMCS_GUEST_RIP, vmread(VMCS_GUEST_RIP) + length);
+    uint64_t interruptibility = vmread(VMCS_GUEST_INTERRUPTIBILITY);
+    vmwrite(VMCS_GUEST_INTERRUPTIBILITY, interruptibility & ~(uint64_t)(INTERRUPTIBILITY_STI | INTERRUPTIBILITY_MOV_SS));
+}
+
+static void vmx_interrupt_window(bool open)
+{
+    uint64_t controls = vmread(VMCS_PROC_CONTROLS);
+    vmwrite(VMCS_PROC_CONTROLS, open ? (controls | PROC_INTERRUPT_WINDOW) : (controls & ~(uint64_t)PROC_INTERRUPT_WINDOW));
+}
+
+// Inject the pending event, else the highest interrupt if the guest can
+// take it, else ask for an exit once it can
+static void vmx_inject(hv_vcpu_t *vcpu, vmx_vcpu_t *v)
+{
+    int vector = hv_irq_next(vcpu);
+    if (v->event_info & INTR_INFO_VALID)
+    {
+        vmwrite(VMCS_ENTRY_INTR_INFO, v->event_info);
+        vmwrite(VMCS_ENTRY_EXCEPTION_ERROR, v->event_error);
+        vmwrite(VMCS_ENTRY_INSTRUCTION_LEN, v->event_length);
+        v->event_info = 0;
+        vmx_interrupt_window(vector >= 0);
+        return;
+    }
+    if (vector < 0)
+    {
+        vmx_interrupt_window(false);
+        return;
+    }
+
+    bool blocked = vmread(VMCS_GUEST_INTERRUPTIBILITY) & (INTERRUPTIBILITY_STI | INTERRUPTIBILITY_MOV_SS);
+    if ((vmread(VMCS_GUEST_RFLAGS) & RFLAGS_IF) && !blocked)
+    {
+        vmwrite(VMCS_ENTRY_INTR_INFO, (uint32_t)vector | INTR_TYPE_EXTERNAL | INTR_INFO_VALID);
+        hv_irq_ack(vcpu, (uint32_t)vector);
+        vmx_interrupt_window(false);
+    }
+    else
+    {
+        vmx_interrupt_window(true);
+    }
+}
+
+// Finish the IN, OUT or MSR access the VMM handled
+static void vmx_complete(hv_vcpu_t *vcpu, vmx_vcpu_t *v)
+{
+    const hv_exit_t *exit = &vcpu->exit;
+    const hv_exit_t *done = &vcpu->completion;
+
+    if (exit->reason == HV_EXIT_IO)
+    {
+        if (!(exit->io.flags & HV_IO_WRITE))
+        {
+            uint64_t data = done->io.data;
+            switch (exit->io.size)
+            {
+            case 1:
+                v->regs.rax = (v->regs.rax & ~0xFFULL) | (data & 0xFF);
+                break;
+            case 2:
+                v->regs.rax = (v->regs.rax & ~0xFFFFULL) | (data & 0xFFFF);
+                break;
+            default:
+                v->regs.rax = (uint32_t)data;
+                break;
+            }
+        }
+        vmx_step(v->step);
+    }
+    else if (exit->reason == HV_EXIT_MSR)
+    {
+        if (done->msr.flags & HV_MSR_FAULT)
+        {
+            vmx_queue_exception(v, VECTOR_GP, true, 0);
+            return;
+        }
+        if (!(exit->msr.flags & HV_MSR_WRITE))
+        {
+            v->regs.rax = (uint32_t)done->msr.value;
+            v->regs.rdx = done->msr.value >> 32;
+        }
+        vmx_step(v->step);
+    }
+}
+
+static uint64_t *vmx_gpr(vmx_vcpu_t *v, uint32_t number)
+{
+    // Instruction encoding order: RAX, RCX, RDX, RBX, RSP, RBP, RSI, RDI, R8...
+    static const uint8_t slots[8] = {0, 2, 3, 1, 7, 6, 4, 5};
+    uint64_t *regs = (uint64_t *)&v->regs;
+    return &regs[number < 8 ? slots[number] : number];
+}
+
+static uint32_t vmx_cr_access(vmx_vcpu_t *v, uint64_t qualification, uint32_t length, hv_exit_t *exit)
+{
+    uint32_t cr = qualification & 0xF;
+    uint32_t type = (qualification >> 4) & 3;
+    uint32_t reg = (qualification >> 8) & 0xF;
+    uint64_t *gpr = vmx_gpr(v, reg);
+
+    if (type == 0 || type == 1)
+    {
+        // RSP lives in the VMCS
+        if (reg == 4)
+        {
+            v->regs.rsp = vmread(VMCS_GUEST_RSP);
+        }
+        if (type == 1 && cr == 3)
+        {
+            *gpr = vmread(VMCS_GUEST_CR3);
+            if (reg == 4)
+            {
+                vmwrite(VMCS_GUEST_RSP, *gpr);
+            }
+            vmx_step(length);
+            return HV_EXIT_NONE;
+        }
+        if (type == 0)
+        {
+            switch (cr)
+            {
+            case 0:
+                vmx_set_cr0(*gpr);
+                vmx_set_efer(vmread(VMCS_GUEST_EFER), *gpr);
+                vmx_step(length);
+                return HV_EXIT_NONE;
+            case 3:
+                vmwrite(VMCS_GUEST_CR3, *gpr);
+                vmx_step(length);
+                return HV_EXIT_NONE;
+            case 4:
+                vmx_set_cr4(*gpr);
+                vmx_step(length);
+                return HV_EXIT_NONE;
+            default:
+                break;
+            }
+        }
+    }
+    exit->hw.code = EXIT_CR_ACCESS;
+    exit->hw.qualification = qualification;
+    return HV_EXIT_UNKNOWN;
+}
+
+static uint32_t vmx_msr(hv_vcpu_t *vcpu, vmx_vcpu_t *v, bool write, uint32_t length, hv_exit_t *exit)
+{
+    uint32_t index = (uint32_t)v->regs.rcx;
+    uint64_t value = (v->regs.rdx << 32) | (uint32_t)v->regs.rax;
+    uint64_t *switched = vmx_switched_msr(v, index);
+    (void)vcpu;
+
+    if (switched)
+    {
+        if (write)
+        {
+            *switched = value;
+        }
+        value = *switched;
+    }
+    else
+    {
+        switch (index)
+        {
+        case MSR_EFER:
+            if (write)
+            {
+                vmx_set_efer(value, vmx_guest_cr0());
+            }
+            value = vmread(VMCS_GUEST_EFER);
+            break;
+        case MSR_IA32_TSC:
+            value = arch_get_rdtsc();
+            break;
+        case MSR_IA32_PAT:
+            v->pat = write ? value : v->pat;
+            value = v->pat;
+            break;
+        case MSR_FS_BASE:
+            if (write)
+            {
+                vmwrite(VMCS_GUEST_ES_BASE + 2 * SEG_FS, value);
+            }
+            value = vmread(VMCS_GUEST_ES_BASE + 2 * SEG_FS);
+            break;
+        case MSR_GS_BASE:
+            if (write)
+            {
+                vmwrite(VMCS_GUEST_ES_BASE + 2 * SEG_GS, value);
+            }
+            value = vmread(VMCS_GUEST_ES_BASE + 2 * SEG_GS);
+            break;
+        case MSR_IA32_SYSENTER_CS:
+        case MSR_IA32_SYSENTER_ESP:
+        case MSR_IA32_SYSENTER_EIP:
+        {
+            uint64_t field = index == MSR_IA32_SYSENTER_CS    ? VMCS_GUEST_SYSENTER_CS
+                             : index == MSR_IA32_SYSENTER_ESP ? VMCS_GUEST_SYSENTER_ESP
+                                                               : VMCS_GUEST_SYSENTER_EIP;
+            if (write)
+            {
+                vmwrite(field, value);
+            }
+            value = vmread(field);
+            break;
+        }
+        default:
+            exit->msr.index = index;
+            exit->msr.flags = write ? HV_MSR_WRITE : 0;
+            exit->msr.value = write ? value : 0;
+            v->step = length;
+            return HV_EXIT_MSR;
+        }
+    }
+
+    if (!write)
+    {
+        v->regs.rax = (uint32_t)value;
+        v->regs.rdx = value >> 32;
+    }
+    vmx_step(length);
+    return HV_EXIT_NONE;
+}
+
+static uint32_t vmx_handle_exit(hv_vcpu_t *vcpu, vmx_vcpu_t *v, hv_exit_t *exit)
+{
+    uint32_t raw = (uint32_t)vmread(VMCS_EXIT_REASON);
+    uint64_t qualification = vmread(VMCS_EXIT_QUALIFICATION);
+    uint32_t length = (uint32_t)vmread(VMCS_EXIT_INSTRUCTION_LEN);
+
+    // An event whose delivery the exit interrupted is delivered again
+    uint32_t vectoring = (uint32_t)vmread(VMCS_IDT_VECTORING_INFO);
+    if (vectoring & INTR_INFO_VALID)
+    {
+        v->event_info = vectoring & (INTR_INFO_VALID | INTR_INFO_ERROR | INTR_TYPE_MASK | 0xFF);
+        v->event_error = (uint32_t)vmread(VMCS_IDT_VECTORING_ERROR);
+        v->event_length = length;
+    }
+
+    if (raw & EXIT_ENTRY_FAILURE)
+    {
+        exit->hw.code = raw & 0xFFFF;
+        exit->hw.qualification = qualification;
+        return HV_EXIT_FAIL_ENTRY;
+    }
+
+    uint32_t reason = raw & 0xFFFF;
+    switch (reason)
+    {
+    case EXIT_EXCEPTION_NMI:
+        // Only NMIs exit: hand them to the host
+        if ((vmread(VMCS_EXIT_INTR_INFO) & INTR_TYPE_MASK) == INTR_TYPE_NMI)
+        {
+            __asm__ volatile("int $2");
+        }
+        return HV_EXIT_NONE;
+    case EXIT_EXTERNAL_INTERRUPT:
+        return HV_EXIT_NONE;
+    case EXIT_TRIPLE_FAULT:
+        return HV_EXIT_SHUTDOWN;
+    case EXIT_INTERRUPT_WINDOW:
+        vmx_interrupt_window(false);
+        return HV_EXIT_NONE;
+    case EXIT_CPUID:
+    {
+        uint32_t out[4];
+        virt_cpuid(vcpu, (uint32_t)v->regs.rax, (uint32_t)v->regs.rcx, out);
+        v->regs.rax = out[0];
+        v->regs.rbx = out[1];
+        v->regs.rcx = out[2];
+        v->regs.rdx = out[3];
+        vmx_step(length);
+        return HV_EXIT_NONE;
+    }
+    case EXIT_HLT:
+        vmx_step(length);
+        if (hv_irq_next(vcpu) >= 0 && (vmread(VMCS_GUEST_RFLAGS) & RFLAGS_IF))
+        {
+            return HV_EXIT_NONE;
+        }
+        return HV_EXIT_HLT;
+    case EXIT_CR_ACCESS:
+        return vmx_cr_access(v, qualification, length, exit);
+    case EXIT_IO:
+        exit->io.port = (uint16_t)(qualification >> 16);
+        exit->io.size = (uint8_t)((qualification & 7) + 1);
+        exit->io.flags = (qualification & (1 << 3)) ? 0 : HV_IO_WRITE;
+        exit->io.flags |= (qualification & (1 << 4)) ? HV_IO_STRING : 0;
+        exit->io.flags |= (qualification & (1 << 5)) ? HV_IO_REP : 0;
+        if ((exit->io.flags & HV_IO_WRITE) && !(exit->io.flags & HV_IO_STRING))
+        {
+            exit->io.data = v->regs.rax & (exit->io.size == 4 ? 0xFFFFFFFFULL : (1ULL << (exit->io.size * 8)) - 1);
+        }
+        v->step = length;
+        return HV_EXIT_IO;
+    case EXIT_RDMSR:
+    case EXIT_WRMSR:
+        return vmx_msr(vcpu, v, reason == EXIT_WRMSR, length, exit);
+    case EXIT_EPT_VIOLATION:
+        exit->mmio.gpa = vmread(VMCS_GUEST_PHYSICAL_ADDRESS);
+        exit->mmio.access = (uint32_t)(qualification & 7); // Read, write, fetch as HV_ACCESS_*
+        if (qualification & (1 << 7))
+        {
+            exit->mmio.gla = vmread(VMCS_GUEST_LINEAR_ADDRESS);
+            exit->mmio.access |= HV_ACCESS_LINEAR;
+        }
+        return HV_EXIT_MMIO;
+    case EXIT_INVEPT:
+    case EXIT_INVVPID:
+    case EXIT_XSETBV:
+        vmx_queue_exception(v, VECTOR_UD, false, 0);
+        return HV_EXIT_NONE;
+    default:
+        if (reason >= EXIT_VMCALL && reason <= EXIT_VMXON)
+        {
+            // No nested virtualization
+            vmx_queue_exception(v, VECTOR_UD, false, 0);
+            return HV_EXIT_NONE;
+        }
+        exit->hw.code = reason;
+        exit->hw.qualification = qualification;
+        return HV_EXIT_UNKNOWN;
+    }
+}
+
+static uint32_t vmx_run(hv_vcpu_t *vcpu, hv_exit_t *exit)
+{
+    vmx_vcpu_t *v = vcpu->backend;
+    if (vmptrld(v->vmcs) != OR_OK)
+    {
+        exit->hw.code = 0;
+        return HV_EXIT_FAIL_ENTRY;
+    }
+    vmx_write_host_state(v);
+
+    if (vcpu->complete)
+    {
+        vmx_complete(vcpu, v);
+        vcpu->complete = false;
+    }
+    vmx_inject(vcpu, v);
+    if (hv_tlb_flush_needed(vcpu, arch_get_current_cpu()))
+    {
+        invept(g_invept_type, vmx_eptp(vcpu->vm));
+    }
+
+    write_cr2(v->cr2);
+    int failed = vmx_enter(&v->regs);
+    v->cr2 = read_cr2();
+
+    uint32_t reason;
+    if (failed)
+    {
+        exit->hw.code = vmread(VMCS_INSTRUCTION_ERROR);
+        reason = HV_EXIT_FAIL_ENTRY;
+    }
+    else
+    {
+        reason = vmx_handle_exit(vcpu, v, exit);
+    }
+    vmclear(v->vmcs);
+    return reason;
+}
+
+static const hv_backend_t vmx_backend = {
+    .name = "vmx",
+    .cpu_enable = vmx_cpu_enable,
+    .vm_init = vmx_vm_init,
+    .vm_destroy = vmx_vm_destroy,
+    .map = vmx_map,
+    .unmap = vmx_unmap,
+    .vcpu_init = vmx_vcpu_init,
+    .vcpu_destroy = vmx_vcpu_destroy,
+    .get_state = vmx_get_state,
+    .set_state = vmx_set_state,
+    .vcpu_load = virt_vcpu_load,
+    .vcpu_put = virt_vcpu_put,
+    .run = vmx_run,
+};
+
+int vmx_init(void)
+{
+    uint32_t eax, ebx, ecx, edx;
+    cpuid(1, &eax, &ebx, &ecx, &edx);
+    if (!(ecx & (1U << 5)) || !vmx_feature_control())
+    {
+        return -OR_ENODEV;
+    }
+
+    uint64_t basic = msr_read(MSR_IA32_VMX_BASIC);
+    bool true_controls = (basic & VMX_BASIC_TRUE_CTLS) != 0;
+    g_revision = (uint32_t)basic & 0x7FFFFFFF;
+
+    bool ok = vmx_controls(true_controls ? MSR_IA32_VMX_TRUE_PINBASED_CTLS : MSR_IA32_VMX_PINBASED_CTLS,
+                           PIN_EXTERNAL_INTERRUPT | PIN_NMI, &g_pin);
+    ok = ok && vmx_controls(true_controls ? MSR_IA32_VMX_TRUE_PROCBASED_CTLS : MSR_IA32_VMX_PROCBASED_CTLS,
+                            PROC_HLT | PROC_UNCONDITIONAL_IO | PROC_SECONDARY, &g_proc);
+    ok = ok && vmx_controls(MSR_IA32_VMX_PROCBASED_CTLS2, PROC2_EPT | PROC2_UNRESTRICTED, &g_proc2);
+    ok = ok && vmx_controls(true_controls ? MSR_IA32_VMX_TRUE_EXIT_CTLS : MSR_IA32_VMX_EXIT_CTLS,
+                            EXIT_HOST_64 | EXIT_SAVE_EFER | EXIT_LOAD_EFER, &g_exit);
+    ok = ok && vmx_controls(true_controls ? MSR_IA32_VMX_TRUE_ENTRY_CTLS : MSR_IA32_VMX_ENTRY_CTLS,
+                            ENTRY_LOAD_EFER, &g_entry);
+    uint64_t ept = msr_read(MSR_IA32_VMX_EPT_VPID_CAP);
+    ok = ok && (ept & EPT_CAP_WALK_4) && (ept & EPT_CAP_WB) && (ept & EPT_CAP_INVEPT) &&
+         (ept & (EPT_CAP_INVEPT_SINGLE | EPT_CAP_INVEPT_ALL));
+    if (!ok)
+    {
+        kinfo("VMX present without EPT or unrestricted guests, not used");
+        return -OR_ENOTSUP;
+    }
+    g_invept_type = (ept & EPT_CAP_INVEPT_SINGLE) ? INVEPT_SINGLE : INVEPT_ALL;
+    // Decided at each entry
+    g_proc &= ~PROC_INTERRUPT_WINDOW;
+    g_entry &= ~ENTRY_IA32E;
+
+    g_cr0_fixed0 = msr_read(MSR_IA32_VMX_CR0_FIXED0);
+    g_cr0_fixed1 = msr_read(MSR_IA32_VMX_CR0_FIXED1);
+    g_cr4_fixed0 = msr_read(MSR_IA32_VMX_CR4_FIXED0);
+    g_cr4_fixed1 = msr_read(MSR_IA32_VMX_CR4_FIXED1);
+
+    return hv_register_backend(&vmx_backend);
+}
@@ -16,6 +16,7 @@
 #include <orion/mm.h>
 #include <orion/structures.h>
 #include <arch.h>
+#include "sandbox.h"
 
 // ========================================
 // SECURITY CONSTANTS
@@ -118,6 +119,8 @@ typedef struct security_context
     uint64_t max_files;          // File limit
     uint64_t max_network_conn;   // Network connection limit
     bool sandboxed;              // Process sandboxed
+    bool has_profile;            // Sandbox profile attached
+    sandbox_profile_t profile;   // Sandbox profile (valid if has_profile)
     uint64_t jail_root;          // Jail root
     atomic64_t violation_count;  // Violations detected
     uint64_t created_time;       // Creation time
@@ -147,6 +150,13 @@ typedef struct audit_entry
 #define AUDIT_SYSCALL_DENIED 6
 #define AUDIT_MEMORY_VIOLATION 7
 #define AUDIT_SECURITY_BREACH 8
+#define AUDIT_SANDBOX_VIOLATION 9
+#define AUDIT_SANDBOX_APPLIED 10
+#define AUDIT_MAC_DENIED 11
+#define AUDIT_MAC_POLICY 12
+
+// First event type available to user-space servers through SYS_AUDIT_EMIT
+#define AUDIT_USER_BASE 0x1000
 
 // Entropy pool for secure random generation
 typedef struct entropy_pool
@@ -698,6 +708,14 @@ int cap_grant(or_cap_t cap_id, uint64_t target_pid, uint64_t rights, uint64_t ca
         return -OR_EPERM;
     }
 
+    // Device windows must fall inside the target's sandbox profile
+    if (source_cap->type == CAP_TYPE_HARDWARE_RESOURCE &&
+        !security_check_bus_access(target_pid, source_cap->object_id, 1))
+    {
+        audit_log_event(AUDIT_CAP_VIOLATION, 6, cap_id, target_pid, 0, "Grant outside sandbox profile");
+        return -OR_EACCES;
+    }
+
     // Ensure target process has security context
     security_context_t *target_ctx = security_get_context(target_pid);
     if (!target_ctx)
@@ -986,6 +1004,44 @@ bool security_validate_cfi_jump(uint64_t source, uint64_t target)
 // SECURITY POLICY ENFORCEMENT
 // ========================================
 
+static void security_terminate_process(uint64_t pid)
+{
+    kwarning("Process PID %llu terminated due to security violations",
+             (unsigned long long)pid);
+
+    // Terminate the process due to security violations
+    process_t *process = scheduler_find_process(pid);
+    if (process)
+    {
+        // Mark process for termination
+        process->state = 4;       // PROCESS_STATE_TERMINATING (assuming this is the value)
+        process->exit_code = 139; // SIGSEGV equivalent
+
+        // Wake up process to handle termination
+        scheduler_wakeup_process(process);
+
+        kinfo("Process PID %llu marked for termination due to security violations",
+              (unsigned long long)pid);
+    }
+    else
+    {
+        kerror("Failed to find process PID %llu for termination",
+               (unsigned long long)pid);
+    }
+}
+
+// Account a sandbox violation; the caller must not hold ctx->lock
+static void sandbox_violation(security_context_t *ctx, uint64_t object_id, const char *description)
+{
+    audit_log_event(AUDIT_SANDBOX_VIOLATION, 6, 0, object_id, ctx->pid, description);
+    atomic_fetch_add(&ctx->violation_count, 1);
+
+    if (ctx->has_profile && (ctx->profile.flags & SANDBOX_FLAG_TERMINATE))
+    {
+        security_terminate_process(ctx->pid);
+    }
+}
+
 bool security_check_syscall_allowed(uint64_t syscall_num, uint64_t pid)
 {
     security_context_t *ctx = security_get_context(pid);
@@ -1007,7 +1063,14 @@ bool security_check_syscall_allowed(uint64_t syscall_num, uint64_t pid)
         audit_log_event(AUDIT_SYSCALL_DENIED, 5, 0, syscall_num, pid,
                         "Syscall denied by security policy");
 
-        atomic_fetch_add(&ctx->violation_count, 1);
+        if (ctx->has_profile)
+        {
+            sandbox_violation(ctx, syscall_num, "Syscall outside sandbox profile");
+        }
+        else
+        {
+            atomic_fetch_add(&ctx->violation_count, 1);
+        }
         return false;
     }
 
@@ -1053,12 +1116,117 @@ bool security_check_memory_limit(uint64_t pid, uint64_t requested_bytes)
     {
         audit_log_event(AUDIT_MEMORY_VIOLATION, 4, 0, pid, requested_bytes,
                         "Memory limit exceeded");
+
+        if (ctx->has_profile)
+        {
+            sandbox_violation(ctx, requested_bytes, "Memory above sandbox ceiling");
+        }
         return false;
     }
 
     return true;
 }
 
+// ========================================
+// SANDBOX PROFILES
+// ========================================
+
+int security_sandbox_apply(uint64_t pid, const sandbox_profile_t *profile)
+{
+    if (!profile || profile->peer_count > SANDBOX_MAX_PEERS ||
+        profile->bus_range_count > SANDBOX_MAX_BUS_RANGES)
+    {
+        return -OR_EINVAL;
+    }
+
+    security_context_t *ctx = security_get_context(pid);
+    if (!ctx)
+    {
+        ctx = security_create_context(pid, SECURITY_LEVEL_RESTRICTED);
+        if (!ctx)
+        {
+            return -OR_ENOMEM;
+        }
+    }
+
+    spinlock_lock(&ctx->lock);
+
+    // Profiles are immutable, otherwise a compromised process could relax its own
+    if (ctx->has_profile)
+    {
+        spinlock_unlock(&ctx->lock);
+        return -OR_EPERM;
+    }
+
+    memcpy(&ctx->profile, profile, sizeof(*profile));
+    for (uint32_t i = 0; i < SANDBOX_SYSCALL_WORDS; i++)
+    {
+        ctx->denied_syscalls[i] |= ~profile->allowed_syscalls[i];
+    }
+    if (profile->max_memory != 0 && profile->max_memory < ctx->max_memory)
+    {
+        ctx->max_memory = profile->max_memory;
+    }
+    ctx->sandboxed = true;
+    ctx->has_profile = true;
+
+    spinlock_unlock(&ctx->lock);
+
+    audit_log_event(AUDIT_SANDBOX_APPLIED, 2, 0, pid, profile->flags, "Sandbox profile attached");
+    kdebug("Sandbox profile attached to PID %llu (%u peers, %u bus ranges)",
+           (unsigned long long)pid, profile->peer_count, profile->bus_range_count);
+
+    return OR_OK;
+}
+
+bool security_check_ipc_allowed(uint64_t pid, uint64_t peer_pid)
+{
+    security_context_t *ctx = security_get_context(pid);
+    if (!ctx || !ctx->has_profile || (ctx->profile.flags & SANDBOX_FLAG_ANY_PEER))
+    {
+        return true;
+    }
+
+    for (uint32_t i = 0; i < ctx->profile.peer_count; i++)
+    {
+        if (ctx->profile.peers[i] == peer_pid)
+        {
+            return true;
+        }
+    }
+
+    sandbox_violation(ctx, peer_pid, "IPC to endpoint outside sandbox profile");
+    return false;
+}
+
+bool security_check_bus_access(uint64_t pid, uint64_t address, uint64_t size)
+{
+    security_context_t *ctx = security_get_context(pid);
+    if (!ctx || !ctx->has_profile)
+    {
+        return true;
+    }
+
+    for (uint32_t i = 0; i < ctx->profile.bus_range_count; i++)
+    {
+        const sandbox_bus_range_t *range = &ctx->profile.bus_ranges[i];
+        if (address >= range->base && size <= range->size &&
+            address - range->base <= range->size - size)
+        {
+            return true;
+        }
+    }
+
+    sandbox_violation(ctx, address, "Device window outside sandbox profile");
+    return false;
+}
+
+bool security_is_sandboxed(uint64_t pid)
+{
+    security_context_t *ctx = security_get_context(pid);
+    return ctx && ctx->sandboxed;
+}
+
 // ========================================
 // PUBLIC SECURITY API
 // ========================================
@@ -1129,30 +1297,26 @@ void security_report_violation(const char *type, uint64_t severity,
         // Consider process termination for severe violations
         if (severity >= 9 && atomic_load(&ctx->violation_count) > 5)
         {
-            kwarning("Process PID %llu terminated due to security violations",
-                     (unsigned long long)pid);
+            security_terminate_process(pid);
+        }
+    }
+}
 
-            // Terminate the process due to security violations
-            process_t *process = scheduler_find_process(pid);
-            if (process)
-            {
-                // Mark process for termination
-                process->state = 4;       // PROCESS_STATE_TERMINATING (assuming this is the value)
-                process->exit_code = 139; // SIGSEGV equivalent
+int security_audit_user_event(uint32_t event_type, const char *description)
+{
+    // Kernel event types cannot be forged from user space
+    if (event_type < AUDIT_USER_BASE || !description)
+    {
+        return -OR_EINVAL;
+    }
 
-                // Wake up process to handle termination
-                scheduler_wakeup_process(process);
+    audit_log_event(event_type, 0, 0, 0, 0, description);
+    return OR_OK;
+}
 
-                kinfo("Process PID %llu marked for termination due to security violations",
-                      (unsigned long long)pid);
-            }
-            else
-            {
-                kerror("Failed to find process PID %llu for termination",
-                       (unsigned long long)pid);
-            }
-        }
-    }
+void security_audit_mac_event(bool denial, uint64_t pid, uint64_t detail, const char *description)
+{
+    audit_log_event(denial ? AUDIT_MAC_DENIED : AUDIT_MAC_POLICY, denial ? 4 : 3, 0, pid, detail, description);
 }
 
 void security_get_stats(uint64_t *capabilities_active, uint64_t *violations_total,
@@ -56,6 +56,24 @@ extern "C"
     bool cap_check_rights(or_cap_t cap, uint64_t rights, uint64_t target);
     void cap_destroy(or_cap_t cap);
 
+    /**
+     * Record an event emitted by a user-space server in the audit log
+     *
+     * @param event_type Event type, at least AUDIT_USER_BASE
+     * @param description NUL-terminated event description
+     * @return 0 on success, -OR_EINVAL for reserved event types
+     */
+    int security_audit_user_event(uint32_t event_type, const char *description);
+
+    /**
+     * Record a mandatory access control event in the audit log (see mac.h)
+     *
+     * @param denial A denial of `pid`, with the permissions denied as
+     *               `detail`; otherwise a policy change
+     * @param description NUL-terminated event description
+     */
+    void security_audit_mac_event(bool denial, uint64_t pid, uint64_t detail, const char *description);
+
 #ifdef __cplusplus
 }
 #endif
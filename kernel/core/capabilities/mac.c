@@ -0,0 +1,547 @@
+/*
+ * Orion Operating System - Mandatory Access Control
+ *
+ * The policy is kept in fixed tables replaced whole by mac_load, so a
+ * check never sees half of a policy. Process labels are kept aside from
+ * the process structures, the way namespace membership is: a process
+ * without an entry is unlabeled, which is what every process is until a
+ * policy labels it. Labels are stored by index and carried across a
+ * policy load by name.
+ *
+ * Denials are recorded once per subject label, object label and class;
+ * a record only gathers the permissions and the count of later ones, so
+ * the table holds what a policy is missing rather than a history. Every
+ * enforced denial is audited, a permissive one only when it adds to its
+ * record.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/scheduler.h>
+#include <orion/capabilities.h>
+#include "mac.h"
+
+// Room for the longest description; the audit log keeps its first 128 bytes
+#define MAC_AUDIT_MAX 256
+
+// Labeled processes; pid 0 marks a free slot
+typedef struct mac_process
+{
+    uint64_t pid;
+    uint32_t label;
+} mac_process_t;
+
+static mac_label_t g_labels[MAC_MAX_LABELS];
+static mac_context_t g_subjects[MAC_MAX_SUBJECT_CONTEXTS];
+static mac_context_t g_objects[MAC_MAX_OBJECT_CONTEXTS];
+static mac_rule_t g_rules[MAC_MAX_RULES];
+static uint32_t g_label_count;
+static uint32_t g_subject_count;
+static uint32_t g_object_count;
+static uint32_t g_rule_count;
+
+static mac_process_t g_processes[MAC_MAX_PROCESSES];
+static mac_denial_t g_denials[MAC_MAX_DENIALS];
+static uint32_t g_denial_count;
+
+static uint32_t g_mode = MAC_MODE_DISABLED;
+static uint64_t g_checks;
+static uint64_t g_denied;
+static uint64_t g_generation;
+static spinlock_t g_mac_lock = SPINLOCK_INIT;
+
+static const char *const g_class_names[MAC_CLASS_MAX + 1] = {
+    "none", "file", "device", "socket", "driver", "process", "policy",
+};
+
+static const char *const g_perm_names[] = {
+    "read", "write", "create", "execute", "bind", "connect", "load", "relabel",
+};
+
+// ========================================
+// HELPERS
+// ========================================
+
+static bool terminated(const char *text, size_t size)
+{
+    for (size_t i = 0; i < size; i++)
+    {
+        if (text[i] == '\0')
+        {
+            return true;
+        }
+    }
+    return false;
+}
+
+static bool pattern_match(const char *pattern, const char *name)
+{
+    size_t length = strlen(pattern);
+    if (length > 0 && pattern[length - 1] == '*')
+    {
+        return strncmp(pattern, name, length - 1) == 0;
+    }
+    return strcmp(pattern, name) == 0;
+}
+
+static void copy_name(char *dest, const char *src, size_t size)
+{
+    strncpy(dest, src, size - 1);
+    dest[size - 1] = '\0';
+}
+
+// Caller holds g_mac_lock
+static int process_slot(uint64_t pid)
+{
+    for (int i = 0; i < MAC_MAX_PROCESSES; i++)
+    {
+        if (g_processes[i].pid == pid)
+        {
+            return i;
+        }
+    }
+    return -1;
+}
+
+// Caller holds g_mac_lock
+static uint32_t label_of(uint64_t pid)
+{
+    int slot = pid ? process_slot(pid) : -1;
+    return slot >= 0 ? g_processes[slot].label : MAC_LABEL_UNLABELED;
+}
+
+// Caller holds g_mac_lock
+static int set_label(uint64_t pid, uint32_t label)
+{
+    int slot = process_slot(pid);
+    if (label == MAC_LABEL_UNLABELED)
+    {
+        if (slot >= 0)
+        {
+            g_processes[slot].pid = 0;
+        }
+        return OR_OK;
+    }
+    if (slot < 0)
+    {
+        slot = process_slot(0);
+        if (slot < 0)
+        {
+            return -OR_ENOSPC;
+        }
+    }
+    g_processes[slot].pid = pid;
+    g_processes[slot].label = label;
+    return OR_OK;
+}
+
+// Label of the first context of `kind` matching `name`, -1 without one.
+// Caller holds g_mac_lock
+static int context_label(const mac_context_t *contexts, uint32_t count, uint32_t kind, const char *name)
+{
+    for (uint32_t i = 0; i < count; i++)
+    {
+        if (contexts[i].kind == kind && pattern_match(contexts[i].pattern, name))
+        {
+            return (int)contexts[i].label;
+        }
+    }
+    return -1;
+}
+
+// Caller holds g_mac_lock
+static int label_by_name(const mac_label_t *labels, uint32_t count, const char *name)
+{
+    for (uint32_t i = 0; i < count; i++)
+    {
+        if (strcmp(labels[i].name, name) == 0)
+        {
+            return (int)i;
+        }
+    }
+    return -1;
+}
+
+// Label of an object; a process object is the label it is moved to.
+// Caller holds g_mac_lock
+static uint32_t object_label(uint32_t object_class, const char *name)
+{
+    int label = object_class == MAC_CLASS_PROCESS ? label_by_name(g_labels, g_label_count, name)
+                                                  : context_label(g_objects, g_object_count, object_class, name);
+    return label < 0 ? MAC_LABEL_UNLABELED : (uint32_t)label;
+}
+
+static void describe_perms(uint32_t perms, char *out, size_t size)
+{
+    size_t used = 0;
+    out[0] = '\0';
+    for (size_t i = 0; i < sizeof(g_perm_names) / sizeof(g_perm_names[0]); i++)
+    {
+        if (perms & (1u << i))
+        {
+            int written = snprintf(out + used, size - used, "%s%s", used ? "," : "", g_perm_names[i]);
+            if (written < 0 || (size_t)written >= size - used)
+            {
+                break;
+            }
+            used += (size_t)written;
+        }
+    }
+}
+
+// Record a denial; whether it is new or adds permissions to its record.
+// Caller holds g_mac_lock
+static bool record_denial(uint32_t subject, uint32_t object, uint32_t object_class, const char *name,
+                          uint32_t perms)
+{
+    for (uint32_t i = 0; i < g_denial_count; i++)
+    {
+        mac_denial_t *denial = &g_denials[i];
+        if (denial->subject == subject && denial->object == object && denial->object_class == object_class)
+        {
+            bool grew = (perms & ~denial->perms) != 0;
+            denial->perms |= perms;
+            denial->count++;
+            return grew;
+        }
+    }
+    if (g_denial_count < MAC_MAX_DENIALS)
+    {
+        mac_denial_t *denial = &g_denials[g_denial_count++];
+        memset(denial, 0, sizeof(*denial));
+        denial->subject = subject;
+        denial->object = object;
+        denial->object_class = object_class;
+        denial->perms = perms;
+        denial->count = 1;
+        copy_name(denial->subject_name, g_labels[subject].name, sizeof(denial->subject_name));
+        copy_name(denial->object_name, g_labels[object].name, sizeof(denial->object_name));
+        copy_name(denial->example, name, sizeof(denial->example));
+    }
+    return true;
+}
+
+// The check itself; on a denial to audit, `audit` holds its description.
+// Caller holds g_mac_lock
+static int check_locked(uint64_t pid, uint32_t object_class, const char *name, uint32_t perms, char *audit)
+{
+    audit[0] = '\0';
+    g_checks++;
+
+    uint32_t subject = label_of(pid);
+    uint32_t object = object_label(object_class, name);
+    uint32_t allowed = 0;
+    for (uint32_t i = 0; i < g_rule_count; i++)
+    {
+        const mac_rule_t *rule = &g_rules[i];
+        if (rule->subject == subject && rule->object == object && rule->object_class == object_class)
+        {
+            allowed |= rule->perms;
+        }
+    }
+    uint32_t missing = perms & ~allowed;
+    if (!missing)
+    {
+        return OR_OK;
+    }
+
+    g_denied++;
+    bool enforced = g_mode == MAC_MODE_ENFORCING && !(g_labels[subject].flags & MAC_LABEL_PERMISSIVE);
+    bool grew = record_denial(subject, object, object_class, name, missing);
+    if (enforced || grew)
+    {
+        char perm_text[64];
+        describe_perms(missing, perm_text, sizeof(perm_text));
+        snprintf(audit, MAC_AUDIT_MAX, "MAC %s %s: %s on %s %s (%s)", enforced ? "denied" : "allowed permissive",
+                 g_labels[subject].name, perm_text, g_class_names[object_class], name, g_labels[object].name);
+    }
+    return enforced ? -OR_EACCES : OR_OK;
+}
+
+// ========================================
+// PUBLIC API
+// ========================================
+
+void mac_init(void)
+{
+    spinlock_init(&g_mac_lock);
+    memset(g_labels, 0, sizeof(g_labels));
+    memset(g_processes, 0, sizeof(g_processes));
+    copy_name(g_labels[MAC_LABEL_UNLABELED].name, "unlabeled", MAC_LABEL_NAME_MAX);
+    g_label_count = 1;
+    g_subject_count = 0;
+    g_object_count = 0;
+    g_rule_count = 0;
+    g_denial_count = 0;
+    g_mode = MAC_MODE_DISABLED;
+    kinfo("MAC: no policy, %d labels and %d rules at most", MAC_MAX_LABELS, MAC_MAX_RULES);
+}
+
+int mac_load(const void *policy, uint64_t size)
+{
+    const mac_policy_header_t *header = (const mac_policy_header_t *)policy;
+    if (!policy || size < sizeof(*header) || header->magic != MAC_POLICY_MAGIC ||
+        header->version != MAC_POLICY_VERSION)
+    {
+        return -OR_EINVAL;
+    }
+    if (header->label_count == 0 || header->label_count > MAC_MAX_LABELS ||
+        header->subject_count > MAC_MAX_SUBJECT_CONTEXTS || header->object_count > MAC_MAX_OBJECT_CONTEXTS ||
+        header->rule_count > MAC_MAX_RULES)
+    {
+        return -OR_EINVAL;
+    }
+    uint64_t expected = sizeof(*header) + header->label_count * sizeof(mac_label_t) +
+                        (uint64_t)(header->subject_count + header->object_count) * sizeof(mac_context_t) +
+                        header->rule_count * sizeof(mac_rule_t);
+    if (size != expected)
+    {
+        return -OR_EINVAL;
+    }
+
+    const mac_label_t *labels = (const mac_label_t *)(header + 1);
+    const mac_context_t *subjects = (const mac_context_t *)(labels + header->label_count);
+    const mac_context_t *objects = subjects + header->subject_count;
+    const mac_rule_t *rules = (const mac_rule_t *)(objects + header->object_count);
+
+    // Everything is checked before anything is replaced
+    for (uint32_t i = 0; i < header->label_count; i++)
+    {
+        if (!terminated(labels[i].name, MAC_LABEL_NAME_MAX) || labels[i].name[0] == '\0')
+        {
+            return -OR_EINVAL;
+        }
+    }
+    for (uint32_t i = 0; i < header->subject_count + header->object_count; i++)
+    {
+        const mac_context_t *context = &subjects[i];
+        bool subject = i < header->subject_count;
+        bool kind_valid = subject ? (context->kind == MAC_SUBJECT_PROGRAM || context->kind == MAC_SUBJECT_USER)
+                                  : (context->kind >= MAC_CLASS_FILE && context->kind <= MAC_CLASS_MAX);
+        if (!kind_valid || context->label >= header->label_count || !terminated(context->pattern, MAC_PATTERN_MAX))
+        {
+            return -OR_EINVAL;
+        }
+    }
+    for (uint32_t i = 0; i < header->rule_count; i++)
+    {
+        const mac_rule_t *rule = &rules[i];
+        if (rule->subject >= header->label_count || rule->object >= header->label_count ||
+            rule->object_class < MAC_CLASS_FILE || rule->object_class > MAC_CLASS_MAX || !rule->perms)
+        {
+            return -OR_EINVAL;
+        }
+    }
+
+    spinlock_lock(&g_mac_lock);
+
+    // Processes keep their labels by name
+    for (int i = 0; i < MAC_MAX_PROCESSES; i++)
+    {
+        mac_process_t *process = &g_processes[i];
+        if (process->pid)
+        {
+            int label = label_by_name(labels, header->label_count, g_labels[process->label].name);
+            if (label <= 0)
+            {
+                process->pid = 0;
+            }
+            else
+            {
+                process->label = (uint32_t)label;
+            }
+        }
+    }
+
+    memcpy(g_labels, labels, header->label_count * sizeof(mac_label_t));
+    memcpy(g_subjects, subjects, header->subject_count * sizeof(mac_context_t));
+    memcpy(g_objects, objects, header->object_count * sizeof(mac_context_t));
+    memcpy(g_rules, rules, header->rule_count * sizeof(mac_rule_t));
+    g_label_count = header->label_count;
+    g_subject_count = header->subject_count;
+    g_object_count = header->object_count;
+    g_rule_count = header->rule_count;
+    g_denial_count = 0;
+    g_mode = (header->flags & MAC_POLICY_ENFORCING) ? MAC_MODE_ENFORCING : MAC_MODE_PERMISSIVE;
+    uint64_t generation = ++g_generation;
+    uint32_t mode = g_mode;
+    spinlock_unlock(&g_mac_lock);
+
+    char audit[MAC_AUDIT_MAX];
+    snprintf(audit, sizeof(audit), "MAC policy %llu loaded: %u labels, %u rules, %s",
+             (unsigned long long)generation, header->label_count, header->rule_count,
+             mode == MAC_MODE_ENFORCING ? "enforcing" : "permissive");
+    security_audit_mac_event(false, 0, generation, audit);
+    kinfo("%s", audit);
+    return OR_OK;
+}
+
+int mac_set_mode(uint32_t mode)
+{
+    if (mode != MAC_MODE_PERMISSIVE && mode != MAC_MODE_ENFORCING)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_mac_lock);
+    if (g_mode == MAC_MODE_DISABLED)
+    {
+        spinlock_unlock(&g_mac_lock);
+        return -OR_ENOENT;
+    }
+    bool changed = g_mode != mode;
+    g_mode = mode;
+    spinlock_unlock(&g_mac_lock);
+
+    if (changed)
+    {
+        security_audit_mac_event(false, 0, mode,
+                                 mode == MAC_MODE_ENFORCING ? "MAC mode: enforcing" : "MAC mode: permissive");
+    }
+    return OR_OK;
+}
+
+int mac_check(uint64_t pid, uint32_t object_class, const char *name, uint32_t perms)
+{
+    if (!name || object_class < MAC_CLASS_FILE || object_class > MAC_CLASS_MAX)
+    {
+        return -OR_EINVAL;
+    }
+
+    char audit[MAC_AUDIT_MAX];
+    spinlock_lock(&g_mac_lock);
+    if (g_mode == MAC_MODE_DISABLED)
+    {
+        spinlock_unlock(&g_mac_lock);
+        return OR_OK;
+    }
+    int result = check_locked(pid, object_class, name, perms, audit);
+    spinlock_unlock(&g_mac_lock);
+
+    if (audit[0])
+    {
+        security_audit_mac_event(true, pid, perms, audit);
+    }
+    return result;
+}
+
+int mac_relabel(uint64_t caller, uint64_t pid, uint32_t kind, const char *name)
+{
+    if (!name || (kind != MAC_SUBJECT_PROGRAM && kind != MAC_SUBJECT_USER))
+    {
+        return -OR_EINVAL;
+    }
+
+    char audit[MAC_AUDIT_MAX];
+    audit[0] = '\0';
+    spinlock_lock(&g_mac_lock);
+    if (g_mode == MAC_MODE_DISABLED)
+    {
+        spinlock_unlock(&g_mac_lock);
+        return OR_OK;
+    }
+    int label = context_label(g_subjects, g_subject_count, kind, name);
+    int result = label < 0 ? -OR_ENOENT : check_locked(caller, MAC_CLASS_PROCESS, g_labels[label].name,
+                                                       MAC_PERM_RELABEL, audit);
+    if (result == OR_OK)
+    {
+        result = set_label(pid, (uint32_t)label);
+    }
+    spinlock_unlock(&g_mac_lock);
+
+    if (audit[0])
+    {
+        security_audit_mac_event(true, caller, MAC_PERM_RELABEL, audit);
+    }
+    return result;
+}
+
+void mac_label_of(uint64_t pid, char *name)
+{
+    spinlock_lock(&g_mac_lock);
+    copy_name(name, g_labels[label_of(pid)].name, MAC_LABEL_NAME_MAX);
+    spinlock_unlock(&g_mac_lock);
+}
+
+int mac_get_denial(uint32_t index, mac_denial_t *denial)
+{
+    if (!denial)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_mac_lock);
+    if (index >= g_denial_count)
+    {
+        spinlock_unlock(&g_mac_lock);
+        return -OR_ENOENT;
+    }
+    *denial = g_denials[index];
+    spinlock_unlock(&g_mac_lock);
+    return OR_OK;
+}
+
+void mac_get_status(mac_status_t *status)
+{
+    spinlock_lock(&g_mac_lock);
+    memset(status, 0, sizeof(*status));
+    status->mode = g_mode;
+    status->labels = g_label_count;
+    status->rules = g_rule_count;
+    for (int i = 0; i < MAC_MAX_PROCESSES; i++)
+    {
+        if (g_processes[i].pid)
+        {
+            status->processes++;
+        }
+    }
+    status->checks = g_checks;
+    status->denials = g_denied;
+    status->generation = g_generation;
+    spinlock_unlock(&g_mac_lock);
+}
+
+int mac_process_fork(uint64_t parent, uint64_t child)
+{
+    spinlock_lock(&g_mac_lock);
+    uint32_t label = label_of(parent);
+    int result = label == MAC_LABEL_UNLABELED ? OR_OK : set_label(child, label);
+    spinlock_unlock(&g_mac_lock);
+    return result;
+}
+
+int mac_process_exec(uint64_t pid, const char *path)
+{
+    if (!path)
+    {
+        return OR_OK;
+    }
+
+    spinlock_lock(&g_mac_lock);
+    int label = context_label(g_subjects, g_subject_count, MAC_SUBJECT_PROGRAM, path);
+    int result = label < 0 ? OR_OK : set_label(pid, (uint32_t)label);
+    spinlock_unlock(&g_mac_lock);
+    return result;
+}
+
+void mac_process_exit(uint64_t pid)
+{
+    if (!pid)
+    {
+        return;
+    }
+
+    spinlock_lock(&g_mac_lock);
+    int slot = process_slot(pid);
+    if (slot >= 0)
+    {
+        g_processes[slot].pid = 0;
+    }
+    spinlock_unlock(&g_mac_lock);
+}
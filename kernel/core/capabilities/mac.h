@@ -0,0 +1,232 @@
+/*
+ * Orion Operating System - Mandatory Access Control
+ *
+ * A system-wide policy checked on top of capabilities: holding the right
+ * capability lets a request reach a server, the policy decides whether
+ * the sender's label may act on the object it names. Subjects are
+ * processes, labeled from the program they run (by executable path) or,
+ * for programs of a login session, by the identity server after the
+ * user they run for; a process keeps its parent's label otherwise.
+ * Objects are named by their server: files by path, devices by their
+ * devfs name, sockets as "tcp:<port>" or "udp:<port>", drivers by name.
+ * Their labels come from the object contexts of the policy, first match
+ * first; objects no context matches, like processes nothing labeled,
+ * carry MAC_LABEL_UNLABELED.
+ *
+ * Policies are written in text and compiled by lib/orion_mac into the
+ * layout below, then loaded with MAC_CTL_LOAD. Rules only allow, so
+ * anything a rule does not allow is denied. Until a policy is loaded
+ * every check passes. In permissive mode, or for a label the policy
+ * marks permissive, denials are audited and recorded but the access
+ * goes through; the recorded denials turn into the rules a policy is
+ * missing (see orion-macctl learn). In enforcing mode they fail with
+ * -OR_EACCES and are audited as well.
+ *
+ * Servers check with MAC_CTL_CHECK, or mac_check() for those built into
+ * the kernel, alongside their capability checks.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_MAC_H
+#define ORION_MAC_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define MAC_MAX_LABELS 64
+#define MAC_MAX_SUBJECT_CONTEXTS 64
+#define MAC_MAX_OBJECT_CONTEXTS 128
+#define MAC_MAX_RULES 512
+#define MAC_MAX_PROCESSES 1024 // Labeled processes at once
+#define MAC_MAX_DENIALS 256    // Distinct denials recorded for learning
+#define MAC_LABEL_NAME_MAX 32  // NUL included
+#define MAC_PATTERN_MAX 96     // NUL included; also the longest object name checked
+
+#define MAC_POLICY_MAGIC 0x43414d4fu // "OMAC"
+#define MAC_POLICY_VERSION 1
+
+// Label 0 of every policy
+#define MAC_LABEL_UNLABELED 0
+
+// Object classes
+#define MAC_CLASS_FILE 1
+#define MAC_CLASS_DEVICE 2
+#define MAC_CLASS_SOCKET 3
+#define MAC_CLASS_DRIVER 4
+#define MAC_CLASS_PROCESS 5 // Object: the label a process is moved to
+#define MAC_CLASS_POLICY 6  // Object: "policy"
+#define MAC_CLASS_MAX 6
+
+// Permissions
+#define MAC_PERM_READ (1 << 0)    // file, device
+#define MAC_PERM_WRITE (1 << 1)   // file, device
+#define MAC_PERM_CREATE (1 << 2)  // file
+#define MAC_PERM_EXECUTE (1 << 3) // file
+#define MAC_PERM_BIND (1 << 4)    // socket: listen or bind a local port
+#define MAC_PERM_CONNECT (1 << 5) // socket: connect to a remote port
+#define MAC_PERM_LOAD (1 << 6)    // driver: install or unload; policy: load or change mode
+#define MAC_PERM_RELABEL (1 << 7) // process
+
+// Subject context kinds
+#define MAC_SUBJECT_PROGRAM 1 // Pattern on the executable path
+#define MAC_SUBJECT_USER 2    // Pattern on the user name of a login session
+
+// Label flags
+#define MAC_LABEL_PERMISSIVE (1 << 0) // Denials of this subject are only recorded
+
+// Modes
+#define MAC_MODE_DISABLED 0 // No policy loaded
+#define MAC_MODE_PERMISSIVE 1
+#define MAC_MODE_ENFORCING 2
+
+// Policy flags
+#define MAC_POLICY_ENFORCING (1 << 0) // Start enforcing rather than permissive
+
+// SYS_MAC_CTL operations
+#define MAC_CTL_LOAD 1     // policy, size: replace the policy
+#define MAC_CTL_SET_MODE 2 // mode: MAC_MODE_PERMISSIVE or MAC_MODE_ENFORCING
+#define MAC_CTL_CHECK 3    // mac_request_t *: 0 or -OR_EACCES
+#define MAC_CTL_RELABEL 4  // mac_request_t *: label the process `pid` by subject context
+#define MAC_CTL_LABEL 5    // pid (0 for the caller), char[MAC_LABEL_NAME_MAX]
+#define MAC_CTL_DENIAL 6   // index, mac_denial_t *
+#define MAC_CTL_STATUS 7   // mac_status_t *
+
+    // Compiled policy, also the MAC_CTL_LOAD ABI: the header, then the
+    // labels, the subject contexts, the object contexts and the rules
+    typedef struct mac_policy_header
+    {
+        uint32_t magic;
+        uint32_t version;
+        uint32_t flags;
+        uint32_t label_count; // MAC_LABEL_UNLABELED included
+        uint32_t subject_count;
+        uint32_t object_count;
+        uint32_t rule_count;
+        uint32_t reserved;
+    } mac_policy_header_t;
+
+    typedef struct mac_label
+    {
+        char name[MAC_LABEL_NAME_MAX];
+        uint32_t flags;
+    } mac_label_t;
+
+    // A pattern matches a name equal to it, or starting with what comes
+    // before a final '*'
+    typedef struct mac_context
+    {
+        uint32_t kind; // Subject kind, or object class
+        uint32_t label;
+        char pattern[MAC_PATTERN_MAX];
+    } mac_context_t;
+
+    typedef struct mac_rule
+    {
+        uint32_t subject; // Labels
+        uint32_t object;
+        uint32_t object_class;
+        uint32_t perms;
+    } mac_rule_t;
+
+#define MAC_POLICY_MAX_SIZE                                                                                \
+    (sizeof(mac_policy_header_t) + MAC_MAX_LABELS * sizeof(mac_label_t) +                                  \
+     (MAC_MAX_SUBJECT_CONTEXTS + MAC_MAX_OBJECT_CONTEXTS) * sizeof(mac_context_t) +                        \
+     MAC_MAX_RULES * sizeof(mac_rule_t))
+
+    // MAC_CTL_CHECK and MAC_CTL_RELABEL request; `kind` is the object
+    // class of a check and the subject kind of a relabel
+    typedef struct mac_request
+    {
+        uint64_t pid;
+        uint32_t kind;
+        uint32_t perms;
+        char name[MAC_PATTERN_MAX];
+    } mac_request_t;
+
+    // A denial recorded for learning: the same subject label denied on
+    // the same object label and class is one record, with the
+    // permissions of every denial
+    typedef struct mac_denial
+    {
+        uint32_t subject;
+        uint32_t object;
+        uint32_t object_class;
+        uint32_t perms;
+        uint64_t count;
+        char subject_name[MAC_LABEL_NAME_MAX];
+        char object_name[MAC_LABEL_NAME_MAX];
+        char example[MAC_PATTERN_MAX]; // First object denied
+    } mac_denial_t;
+
+    typedef struct mac_status
+    {
+        uint32_t mode;
+        uint32_t labels;
+        uint32_t rules;
+        uint32_t processes; // Labeled processes
+        uint64_t checks;
+        uint64_t denials; // Audited, allowed or not
+        uint64_t generation; // Policies loaded since boot
+    } mac_status_t;
+
+    void mac_init(void);
+
+    /**
+     * Replace the policy
+     *
+     * Processes keep their labels across a load by name; labels the new
+     * policy lacks become MAC_LABEL_UNLABELED. Recorded denials are
+     * forgotten.
+     *
+     * @return 0 on success, -OR_EINVAL for a malformed policy, -OR_ENOMEM
+     */
+    int mac_load(const void *policy, uint64_t size);
+
+    int mac_set_mode(uint32_t mode);
+
+    /**
+     * Whether `pid` may act with `perms` on the object `name` of `object_class`
+     *
+     * @return 0 when allowed (or only recorded), -OR_EACCES when denied
+     */
+    int mac_check(uint64_t pid, uint32_t object_class, const char *name, uint32_t perms);
+
+    /**
+     * Label `pid` by the subject context of `kind` matching `name`
+     *
+     * @param caller Process asking, which needs MAC_PERM_RELABEL on the new label
+     * @return 0 on success or without a policy, -OR_ENOENT when no context
+     *         matches, -OR_EACCES when the caller may not
+     */
+    int mac_relabel(uint64_t caller, uint64_t pid, uint32_t kind, const char *name);
+
+    // Name of the label of `pid`, copied into `name` (MAC_LABEL_NAME_MAX bytes)
+    void mac_label_of(uint64_t pid, char *name);
+
+    int mac_get_denial(uint32_t index, mac_denial_t *denial);
+    void mac_get_status(mac_status_t *status);
+
+    // A process was created by `parent` (0 for the kernel) and takes its label
+    int mac_process_fork(uint64_t parent, uint64_t child);
+
+    // The process `pid` is about to run the program at `path`, and takes
+    // the label of its program context if one matches
+    int mac_process_exec(uint64_t pid, const char *path);
+
+    // Forget a process that is being destroyed
+    void mac_process_exit(uint64_t pid);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_MAC_H
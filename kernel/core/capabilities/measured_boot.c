@@ -0,0 +1,260 @@
+/*
+ * Orion Operating System - Measured Boot
+ *
+ * Boot measurement event log. The kernel hashes its own image at startup and
+ * records a SHA-256 entry for every component measured afterwards. The TPM
+ * driver replays the log into the hardware PCRs, so the log and the PCR
+ * values can be checked against each other by a remote verifier.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include "measured_boot.h"
+
+// Immutable part of the kernel image (text and rodata), from linker.ld
+extern const uint8_t __measured_start[];
+extern const uint8_t __measured_end[];
+
+// ========================================
+// SHA-256
+// ========================================
+
+typedef struct sha256_ctx
+{
+    uint32_t state[8];
+    uint64_t length;
+    uint8_t block[64];
+    size_t block_len;
+} sha256_ctx_t;
+
+static const uint32_t sha256_k[64] = {
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2};
+
+#define ROTR32(x, n) (((x) >> (n)) | ((x) << (32 - (n))))
+
+static void sha256_compress(sha256_ctx_t *ctx, const uint8_t *block)
+{
+    uint32_t w[64];
+
+    for (int i = 0; i < 16; i++)
+    {
+        w[i] = ((uint32_t)block[i * 4] << 24) | ((uint32_t)block[i * 4 + 1] << 16) |
+               ((uint32_t)block[i * 4 + 2] << 8) | (uint32_t)block[i * 4 + 3];
+    }
+    for (int i = 16; i < 64; i++)
+    {
+        uint32_t s0 = ROTR32(w[i - 15], 7) ^ ROTR32(w[i - 15], 18) ^ (w[i - 15] >> 3);
+        uint32_t s1 = ROTR32(w[i - 2], 17) ^ ROTR32(w[i - 2], 19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+
+    uint32_t a = ctx->state[0], b = ctx->state[1], c = ctx->state[2], d = ctx->state[3];
+    uint32_t e = ctx->state[4], f = ctx->state[5], g = ctx->state[6], h = ctx->state[7];
+
+    for (int i = 0; i < 64; i++)
+    {
+        uint32_t s1 = ROTR32(e, 6) ^ ROTR32(e, 11) ^ ROTR32(e, 25);
+        uint32_t ch = (e & f) ^ (~e & g);
+        uint32_t t1 = h + s1 + ch + sha256_k[i] + w[i];
+        uint32_t s0 = ROTR32(a, 2) ^ ROTR32(a, 13) ^ ROTR32(a, 22);
+        uint32_t maj = (a & b) ^ (a & c) ^ (b & c);
+        uint32_t t2 = s0 + maj;
+
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+
+    ctx->state[0] += a;
+    ctx->state[1] += b;
+    ctx->state[2] += c;
+    ctx->state[3] += d;
+    ctx->state[4] += e;
+    ctx->state[5] += f;
+    ctx->state[6] += g;
+    ctx->state[7] += h;
+}
+
+static void sha256_init(sha256_ctx_t *ctx)
+{
+    static const uint32_t initial[8] = {
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+        0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19};
+
+    memcpy(ctx->state, initial, sizeof(initial));
+    ctx->length = 0;
+    ctx->block_len = 0;
+}
+
+static void sha256_update(sha256_ctx_t *ctx, const uint8_t *data, size_t size)
+{
+    ctx->length += size;
+
+    while (size > 0)
+    {
+        size_t chunk = 64 - ctx->block_len;
+        if (chunk > size)
+        {
+            chunk = size;
+        }
+
+        memcpy(ctx->block + ctx->block_len, data, chunk);
+        ctx->block_len += chunk;
+        data += chunk;
+        size -= chunk;
+
+        if (ctx->block_len == 64)
+        {
+            sha256_compress(ctx, ctx->block);
+            ctx->block_len = 0;
+        }
+    }
+}
+
+static void sha256_final(sha256_ctx_t *ctx, uint8_t digest[MEASURE_DIGEST_SIZE])
+{
+    uint64_t bit_length = ctx->length * 8;
+
+    ctx->block[ctx->block_len++] = 0x80;
+    if (ctx->block_len > 56)
+    {
+        memset(ctx->block + ctx->block_len, 0, 64 - ctx->block_len);
+        sha256_compress(ctx, ctx->block);
+        ctx->block_len = 0;
+    }
+    memset(ctx->block + ctx->block_len, 0, 56 - ctx->block_len);
+
+    for (int i = 0; i < 8; i++)
+    {
+        ctx->block[56 + i] = (uint8_t)(bit_length >> (56 - i * 8));
+    }
+    sha256_compress(ctx, ctx->block);
+
+    for (int i = 0; i < 8; i++)
+    {
+        digest[i * 4] = (uint8_t)(ctx->state[i] >> 24);
+        digest[i * 4 + 1] = (uint8_t)(ctx->state[i] >> 16);
+        digest[i * 4 + 2] = (uint8_t)(ctx->state[i] >> 8);
+        digest[i * 4 + 3] = (uint8_t)ctx->state[i];
+    }
+}
+
+void measure_sha256(const void *data, size_t size, uint8_t digest[MEASURE_DIGEST_SIZE])
+{
+    sha256_ctx_t ctx;
+
+    sha256_init(&ctx);
+    sha256_update(&ctx, (const uint8_t *)data, size);
+    sha256_final(&ctx, digest);
+}
+
+// ========================================
+// EVENT LOG
+// ========================================
+
+static measure_event_t g_measure_log[MEASURE_LOG_MAX_EVENTS];
+static uint32_t g_measure_count = 0;
+static spinlock_t g_measure_lock = SPINLOCK_INIT;
+
+static int measure_append(uint32_t pcr, uint32_t event_type, const char *description,
+                          const uint8_t digest[MEASURE_DIGEST_SIZE])
+{
+    spinlock_lock(&g_measure_lock);
+
+    if (g_measure_count >= MEASURE_LOG_MAX_EVENTS)
+    {
+        spinlock_unlock(&g_measure_lock);
+        kerror("measured_boot: event log full, dropping '%s'", description);
+        return -OR_ENOMEM;
+    }
+
+    measure_event_t *event = &g_measure_log[g_measure_count];
+    memset(event, 0, sizeof(*event));
+    event->index = g_measure_count;
+    event->pcr = pcr;
+    event->event_type = event_type;
+    memcpy(event->digest, digest, MEASURE_DIGEST_SIZE);
+    strncpy(event->description, description, MEASURE_DESCRIPTION_LEN - 1);
+
+    g_measure_count++;
+
+    spinlock_unlock(&g_measure_lock);
+    return OR_OK;
+}
+
+int measure_buffer(uint32_t pcr, uint32_t event_type, const char *description,
+                   const void *data, size_t size)
+{
+    uint8_t digest[MEASURE_DIGEST_SIZE];
+
+    if (!description || (!data && size != 0) || pcr > 23)
+    {
+        return -OR_EINVAL;
+    }
+
+    measure_sha256(data, size, digest);
+
+    int result = measure_append(pcr, event_type, description, digest);
+    if (result == OR_OK)
+    {
+        kinfo("measured_boot: PCR%u <- %s (%llu bytes, %02x%02x%02x%02x...)",
+              pcr, description, (unsigned long long)size,
+              digest[0], digest[1], digest[2], digest[3]);
+    }
+
+    return result;
+}
+
+int measure_log_get(uint32_t index, measure_event_t *event)
+{
+    if (!event)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_measure_lock);
+
+    if (index >= g_measure_count)
+    {
+        spinlock_unlock(&g_measure_lock);
+        return -OR_ENOENT;
+    }
+
+    memcpy(event, &g_measure_log[index], sizeof(*event));
+
+    spinlock_unlock(&g_measure_lock);
+    return OR_OK;
+}
+
+uint32_t measure_log_count(void)
+{
+    return g_measure_count;
+}
+
+void measured_boot_init(void)
+{
+    size_t image_size = (size_t)(__measured_end - __measured_start);
+
+    kinfo("Initializing measured boot event log");
+
+    measure_buffer(MEASURE_PCR_KERNEL, MEASURE_EVENT_KERNEL_IMAGE, "orion-kernel",
+                   __measured_start, image_size);
+}
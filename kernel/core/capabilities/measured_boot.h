@@ -0,0 +1,90 @@
+/*
+ * Orion Operating System - Measured Boot Header
+ *
+ * Boot measurement event log declarations.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_MEASURED_BOOT_H
+#define ORION_MEASURED_BOOT_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define MEASURE_LOG_MAX_EVENTS 128
+#define MEASURE_DIGEST_SIZE 32 // SHA-256
+#define MEASURE_DESCRIPTION_LEN 64
+
+// PCRs used by Orion (TCG PC Client allocation, OS range)
+#define MEASURE_PCR_KERNEL 8
+#define MEASURE_PCR_SERVERS 9
+#define MEASURE_PCR_CONFIG 10
+
+// Event types
+#define MEASURE_EVENT_KERNEL_IMAGE 1
+#define MEASURE_EVENT_SERVER_IMAGE 2
+#define MEASURE_EVENT_CONFIG 3
+#define MEASURE_EVENT_SEPARATOR 4
+
+    // One entry of the measurement log, also the SYS_MEASURE_LOG ABI
+    typedef struct measure_event
+    {
+        uint32_t index;
+        uint32_t pcr;
+        uint32_t event_type;
+        uint32_t reserved;
+        uint8_t digest[MEASURE_DIGEST_SIZE];
+        char description[MEASURE_DESCRIPTION_LEN];
+    } measure_event_t;
+
+    /**
+     * Initialize the event log and measure the running kernel image
+     */
+    void measured_boot_init(void);
+
+    /**
+     * Hash a buffer and append the measurement to the event log
+     *
+     * @param pcr PCR the measurement is destined for
+     * @param event_type MEASURE_EVENT_* value
+     * @param description Human readable name of the measured object
+     * @param data Bytes to measure
+     * @param size Number of bytes
+     * @return 0 on success, negative error code on failure
+     */
+    int measure_buffer(uint32_t pcr, uint32_t event_type, const char *description,
+                       const void *data, size_t size);
+
+    /**
+     * Copy one event log entry
+     *
+     * @param index Entry index
+     * @param event Output entry
+     * @return 0 on success, -OR_ENOENT past the end of the log
+     */
+    int measure_log_get(uint32_t index, measure_event_t *event);
+
+    /**
+     * Number of entries in the event log
+     */
+    uint32_t measure_log_count(void);
+
+    /**
+     * Compute the SHA-256 digest of a buffer
+     */
+    void measure_sha256(const void *data, size_t size, uint8_t digest[MEASURE_DIGEST_SIZE]);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_MEASURED_BOOT_H
@@ -0,0 +1,86 @@
+/*
+ * Orion Operating System - Sandbox Profiles Header
+ *
+ * Per-process sandbox profiles enforced by the capability system. A profile
+ * lists the syscalls a process may issue, the IPC endpoints it may send to,
+ * its memory ceiling and the device address windows it may be granted.
+ * Profiles are built from declarative manifests by the I/O server and
+ * loaded with SYS_SANDBOX_LOAD.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_SANDBOX_H
+#define ORION_SANDBOX_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define SANDBOX_MAX_PEERS 16
+#define SANDBOX_MAX_BUS_RANGES 8
+#define SANDBOX_SYSCALL_WORDS 8
+
+// Profile flags
+#define SANDBOX_FLAG_TERMINATE (1 << 0) // Terminate the process on violation
+#define SANDBOX_FLAG_ANY_PEER (1 << 1)  // No IPC restriction (servers answering arbitrary clients)
+
+    // Device address window (MMIO or DMA) a process may be granted
+    typedef struct sandbox_bus_range
+    {
+        uint64_t base;
+        uint64_t size;
+    } sandbox_bus_range_t;
+
+    // Sandbox profile, also the SYS_SANDBOX_LOAD ABI
+    typedef struct sandbox_profile
+    {
+        uint64_t max_memory;                                    // Bytes, 0 keeps the default
+        uint64_t allowed_syscalls[SANDBOX_SYSCALL_WORDS];       // Bitmap of permitted syscalls
+        uint64_t peers[SANDBOX_MAX_PEERS];                      // Endpoints the process may send to
+        sandbox_bus_range_t bus_ranges[SANDBOX_MAX_BUS_RANGES]; // Device windows it may be granted
+        uint32_t peer_count;
+        uint32_t bus_range_count;
+        uint32_t flags;
+        uint32_t reserved;
+    } sandbox_profile_t;
+
+    /**
+     * Attach a sandbox profile to a process
+     *
+     * A process keeps its first profile for its whole lifetime.
+     *
+     * @param pid Target process
+     * @param profile Profile to enforce
+     * @return 0 on success, -OR_EPERM if a profile is already attached,
+     *         -OR_EINVAL for malformed profiles
+     */
+    int security_sandbox_apply(uint64_t pid, const sandbox_profile_t *profile);
+
+    /**
+     * Check whether a process may send IPC messages to an endpoint
+     */
+    bool security_check_ipc_allowed(uint64_t pid, uint64_t peer_pid);
+
+    /**
+     * Check whether a device address window may be granted to a process
+     */
+    bool security_check_bus_access(uint64_t pid, uint64_t address, uint64_t size);
+
+    /**
+     * Whether a process runs under a sandbox (and may not sandbox others)
+     */
+    bool security_is_sandboxed(uint64_t pid);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_SANDBOX_H
@@ -0,0 +1,551 @@
+/*
+ * Orion Operating System - Flattened Device Tree
+ *
+ * Walks the structure block token by token. Every lookup starts from an
+ * offset and stops at the end of the block, so a malformed blob can make
+ * a lookup fail but never read past total_size.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/fdt.h>
+
+#define FDT_ALIGN(x) (((x) + 3) & ~3)
+
+// Header fields, all big-endian 32-bit words
+#define FDT_HDR_MAGIC 0
+#define FDT_HDR_TOTALSIZE 4
+#define FDT_HDR_OFF_STRUCT 8
+#define FDT_HDR_OFF_STRINGS 12
+#define FDT_HDR_VERSION 20
+#define FDT_HDR_BOOT_CPU 28
+#define FDT_HDR_SIZE_STRINGS 32
+#define FDT_HDR_SIZE_STRUCT 36
+#define FDT_HDR_LEN 40
+
+static fdt_t g_boot_fdt;
+static bool g_boot_fdt_valid = false;
+
+int fdt_init(fdt_t *fdt, const void *blob)
+{
+    const uint8_t *header = (const uint8_t *)blob;
+    if (!fdt || !header || fdt32_to_cpu(header + FDT_HDR_MAGIC) != FDT_MAGIC)
+    {
+        return -OR_EINVAL;
+    }
+    if (fdt32_to_cpu(header + FDT_HDR_VERSION) < FDT_MIN_VERSION)
+    {
+        return -OR_ENOTSUP;
+    }
+
+    uint32_t total = fdt32_to_cpu(header + FDT_HDR_TOTALSIZE);
+    uint32_t off_struct = fdt32_to_cpu(header + FDT_HDR_OFF_STRUCT);
+    uint32_t size_struct = fdt32_to_cpu(header + FDT_HDR_SIZE_STRUCT);
+    uint32_t off_strings = fdt32_to_cpu(header + FDT_HDR_OFF_STRINGS);
+    uint32_t size_strings = fdt32_to_cpu(header + FDT_HDR_SIZE_STRINGS);
+    if (total < FDT_HDR_LEN || off_struct > total || size_struct > total - off_struct || off_strings > total ||
+        size_strings > total - off_strings)
+    {
+        return -OR_EINVAL;
+    }
+
+    fdt->blob = header;
+    fdt->structs = header + off_struct;
+    fdt->strings = (const char *)header + off_strings;
+    fdt->total_size = total;
+    fdt->struct_size = size_struct;
+    fdt->strings_size = size_strings;
+    fdt->boot_cpu = fdt32_to_cpu(header + FDT_HDR_BOOT_CPU);
+    return OR_OK;
+}
+
+int fdt_set_boot(const void *blob)
+{
+    int result = fdt_init(&g_boot_fdt, blob);
+    g_boot_fdt_valid = result == OR_OK;
+    if (g_boot_fdt_valid)
+    {
+        kinfo("FDT: device tree at 0x%p, %u bytes", blob, g_boot_fdt.total_size);
+    }
+    return result;
+}
+
+const fdt_t *fdt_boot(void)
+{
+    return g_boot_fdt_valid ? &g_boot_fdt : NULL;
+}
+
+uint64_t fdt_read_cells(const void *p, uint32_t cells)
+{
+    const uint8_t *bytes = (const uint8_t *)p;
+    uint64_t value = 0;
+    for (uint32_t i = 0; i < cells; i++)
+    {
+        value = (value << 32) | fdt32_to_cpu(bytes + i * 4);
+    }
+    return value;
+}
+
+// ========================================
+// TOKENS
+// ========================================
+
+static size_t fdt_strnlen(const char *s, size_t max)
+{
+    size_t len = 0;
+    while (len < max && s[len])
+    {
+        len++;
+    }
+    return len;
+}
+
+// Read the token at `offset` and return the offset of the next one, or -1
+// when the token runs past the structure block
+static int fdt_next_tag(const fdt_t *fdt, int offset, uint32_t *tag)
+{
+    if (offset < 0 || (uint32_t)offset + 4 > fdt->struct_size)
+    {
+        return -1;
+    }
+    *tag = fdt32_to_cpu(fdt->structs + offset);
+    uint32_t next = offset + 4;
+
+    switch (*tag)
+    {
+    case FDT_BEGIN_NODE:
+    {
+        const char *name = (const char *)fdt->structs + next;
+        size_t len = fdt_strnlen(name, fdt->struct_size - next);
+        if (next + len >= fdt->struct_size)
+        {
+            return -1;
+        }
+        next = FDT_ALIGN(next + len + 1);
+        break;
+    }
+    case FDT_PROP:
+    {
+        if (next + 8 > fdt->struct_size)
+        {
+            return -1;
+        }
+        uint32_t len = fdt32_to_cpu(fdt->structs + next);
+        if (len > fdt->struct_size - next - 8)
+        {
+            return -1;
+        }
+        next = FDT_ALIGN(next + 8 + len);
+        break;
+    }
+    case FDT_END_NODE:
+    case FDT_NOP:
+    case FDT_END:
+        break;
+    default:
+        return -1;
+    }
+    return next <= fdt->struct_size ? (int)next : -1;
+}
+
+int fdt_next_node(const fdt_t *fdt, int offset, int *depth)
+{
+    uint32_t tag;
+    int next = 0;
+
+    if (offset >= 0)
+    {
+        next = fdt_next_tag(fdt, offset, &tag);
+        if (next < 0 || tag != FDT_BEGIN_NODE)
+        {
+            return -1;
+        }
+    }
+
+    for (;;)
+    {
+        int current = next;
+        next = fdt_next_tag(fdt, current, &tag);
+        if (next < 0)
+        {
+            return -1;
+        }
+        switch (tag)
+        {
+        case FDT_BEGIN_NODE:
+            if (depth)
+            {
+                (*depth)++;
+            }
+            return current;
+        case FDT_END_NODE:
+            if (depth && --(*depth) < 0)
+            {
+                return -1;
+            }
+            break;
+        case FDT_END:
+            return -1;
+        default:
+            break;
+        }
+    }
+}
+
+int fdt_first_subnode(const fdt_t *fdt, int parent)
+{
+    int depth = 0;
+    int node = fdt_next_node(fdt, parent, &depth);
+    return (node >= 0 && depth == 1) ? node : -1;
+}
+
+int fdt_next_subnode(const fdt_t *fdt, int offset)
+{
+    int depth = 1;
+    int node = offset;
+    // Skip the children of `offset` until a node at its own level
+    do
+    {
+        node = fdt_next_node(fdt, node, &depth);
+        if (node < 0 || depth < 1)
+        {
+            return -1;
+        }
+    } while (depth > 1);
+    return node;
+}
+
+int fdt_parent_offset(const fdt_t *fdt, int node)
+{
+    int stack[FDT_MAX_DEPTH];
+    int depth = -1;
+
+    for (int offset = fdt_next_node(fdt, -1, &depth); offset >= 0; offset = fdt_next_node(fdt, offset, &depth))
+    {
+        if (depth >= FDT_MAX_DEPTH)
+        {
+            return -1;
+        }
+        stack[depth] = offset;
+        if (offset == node)
+        {
+            return depth > 0 ? stack[depth - 1] : -1;
+        }
+    }
+    return -1;
+}
+
+const char *fdt_get_name(const fdt_t *fdt, int node)
+{
+    if (node < 0 || (uint32_t)node + 4 >= fdt->struct_size)
+    {
+        return NULL;
+    }
+    return (const char *)fdt->structs + node + 4;
+}
+
+// Node names carry a unit address ("virtio_mmio@a000000"); a lookup without
+// one matches any unit address
+static bool fdt_name_matches(const char *name, const char *wanted, size_t wanted_len)
+{
+    if (strncmp(name, wanted, wanted_len) != 0)
+    {
+        return false;
+    }
+    return name[wanted_len] == '\0' || name[wanted_len] == '@';
+}
+
+int fdt_subnode_offset(const fdt_t *fdt, int parent, const char *name)
+{
+    size_t len = strlen(name);
+    for (int node = fdt_first_subnode(fdt, parent); node >= 0; node = fdt_next_subnode(fdt, node))
+    {
+        if (fdt_name_matches(fdt_get_name(fdt, node), name, len))
+        {
+            return node;
+        }
+    }
+    return -1;
+}
+
+int fdt_path_offset(const fdt_t *fdt, const char *path)
+{
+    int depth = -1;
+    int node = fdt_next_node(fdt, -1, &depth);
+    if (!path || path[0] != '/')
+    {
+        return -1;
+    }
+
+    const char *p = path;
+    while (node >= 0 && *p)
+    {
+        while (*p == '/')
+        {
+            p++;
+        }
+        if (!*p)
+        {
+            break;
+        }
+        const char *end = p;
+        while (*end && *end != '/')
+        {
+            end++;
+        }
+
+        char component[64];
+        size_t len = (size_t)(end - p);
+        if (len >= sizeof(component))
+        {
+            return -1;
+        }
+        memcpy(component, p, len);
+        component[len] = '\0';
+        node = fdt_subnode_offset(fdt, node, component);
+        p = end;
+    }
+    return node;
+}
+
+// ========================================
+// PROPERTIES
+// ========================================
+
+const void *fdt_getprop(const fdt_t *fdt, int node, const char *name, uint32_t *len)
+{
+    uint32_t tag;
+    int offset = fdt_next_tag(fdt, node, &tag);
+    if (offset < 0 || tag != FDT_BEGIN_NODE)
+    {
+        return NULL;
+    }
+
+    // Properties come before the subnodes
+    for (;;)
+    {
+        int next = fdt_next_tag(fdt, offset, &tag);
+        if (next < 0)
+        {
+            return NULL;
+        }
+        if (tag == FDT_PROP)
+        {
+            uint32_t prop_len = fdt32_to_cpu(fdt->structs + offset + 4);
+            uint32_t name_off = fdt32_to_cpu(fdt->structs + offset + 8);
+            if (name_off < fdt->strings_size && strcmp(fdt->strings + name_off, name) == 0)
+            {
+                if (len)
+                {
+                    *len = prop_len;
+                }
+                return fdt->structs + offset + 12;
+            }
+        }
+        else if (tag != FDT_NOP)
+        {
+            return NULL;
+        }
+        offset = next;
+    }
+}
+
+bool fdt_getprop_u32(const fdt_t *fdt, int node, const char *name, uint32_t *value)
+{
+    uint32_t len = 0;
+    const void *prop = fdt_getprop(fdt, node, name, &len);
+    if (!prop || len < 4)
+    {
+        return false;
+    }
+    *value = fdt32_to_cpu(prop);
+    return true;
+}
+
+const char *fdt_getprop_string(const fdt_t *fdt, int node, const char *name)
+{
+    uint32_t len = 0;
+    const char *prop = (const char *)fdt_getprop(fdt, node, name, &len);
+    if (!prop || len == 0 || prop[len - 1] != '\0')
+    {
+        return NULL;
+    }
+    return prop;
+}
+
+bool fdt_node_is_compatible(const fdt_t *fdt, int node, const char *compatible)
+{
+    uint32_t len = 0;
+    const char *list = (const char *)fdt_getprop(fdt, node, "compatible", &len);
+    if (!list)
+    {
+        return false;
+    }
+
+    // A NUL-separated list of strings, most specific first
+    uint32_t pos = 0;
+    while (pos < len)
+    {
+        const char *entry = list + pos;
+        size_t entry_len = fdt_strnlen(entry, len - pos);
+        if (strcmp(entry, compatible) == 0 && pos + entry_len < len)
+        {
+            return true;
+        }
+        pos += entry_len + 1;
+    }
+    return false;
+}
+
+bool fdt_node_is_enabled(const fdt_t *fdt, int node)
+{
+    const char *status = fdt_getprop_string(fdt, node, "status");
+    return !status || strcmp(status, "okay") == 0 || strcmp(status, "ok") == 0;
+}
+
+int fdt_node_offset_by_compatible(const fdt_t *fdt, int start, const char *compatible)
+{
+    int node = start;
+    while ((node = fdt_next_node(fdt, node, NULL)) >= 0)
+    {
+        if (fdt_node_is_compatible(fdt, node, compatible))
+        {
+            return node;
+        }
+    }
+    return -1;
+}
+
+int fdt_node_offset_by_phandle(const fdt_t *fdt, uint32_t phandle)
+{
+    if (phandle == 0 || phandle == 0xFFFFFFFF)
+    {
+        return -1;
+    }
+    for (int node = fdt_next_node(fdt, -1, NULL); node >= 0; node = fdt_next_node(fdt, node, NULL))
+    {
+        uint32_t value;
+        if ((fdt_getprop_u32(fdt, node, "phandle", &value) || fdt_getprop_u32(fdt, node, "linux,phandle", &value)) &&
+            value == phandle)
+        {
+            return node;
+        }
+    }
+    return -1;
+}
+
+// ========================================
+// ADDRESSES AND INTERRUPTS
+// ========================================
+
+int fdt_read_reg(const fdt_t *fdt, int node, uint32_t index, uint64_t *addr, uint64_t *size)
+{
+    int parent = fdt_parent_offset(fdt, node);
+    uint32_t address_cells = 2;
+    uint32_t size_cells = 1;
+    if (parent >= 0)
+    {
+        fdt_getprop_u32(fdt, parent, "#address-cells", &address_cells);
+        fdt_getprop_u32(fdt, parent, "#size-cells", &size_cells);
+    }
+    if (address_cells == 0 || address_cells > 2 || size_cells > 2)
+    {
+        return -OR_ENOTSUP;
+    }
+
+    uint32_t len = 0;
+    const uint8_t *reg = (const uint8_t *)fdt_getprop(fdt, node, "reg", &len);
+    uint32_t entry = (address_cells + size_cells) * 4;
+    if (!reg || (uint64_t)(index + 1) * entry > len)
+    {
+        return -OR_ENOENT;
+    }
+
+    reg += index * entry;
+    if (addr)
+    {
+        *addr = fdt_read_cells(reg, address_cells);
+    }
+    if (size)
+    {
+        *size = size_cells ? fdt_read_cells(reg + address_cells * 4, size_cells) : 0;
+    }
+    return OR_OK;
+}
+
+// The controller an interrupt is routed to: the nearest "interrupt-parent"
+// going up from the node
+static int fdt_interrupt_parent(const fdt_t *fdt, int node)
+{
+    for (int current = node; current >= 0; current = fdt_parent_offset(fdt, current))
+    {
+        uint32_t phandle;
+        if (fdt_getprop_u32(fdt, current, "interrupt-parent", &phandle))
+        {
+            return fdt_node_offset_by_phandle(fdt, phandle);
+        }
+    }
+    return -1;
+}
+
+int fdt_read_interrupt(const fdt_t *fdt, int node, uint32_t index, uint32_t *cells, uint32_t max_cells)
+{
+    uint32_t interrupt_cells = 1;
+    int controller = fdt_interrupt_parent(fdt, node);
+    if (controller >= 0)
+    {
+        fdt_getprop_u32(fdt, controller, "#interrupt-cells", &interrupt_cells);
+    }
+    if (interrupt_cells == 0 || interrupt_cells > max_cells)
+    {
+        return -OR_ENOTSUP;
+    }
+
+    uint32_t len = 0;
+    const uint8_t *prop = (const uint8_t *)fdt_getprop(fdt, node, "interrupts", &len);
+    if (!prop || (uint64_t)(index + 1) * interrupt_cells * 4 > len)
+    {
+        return -OR_ENOENT;
+    }
+    prop += index * interrupt_cells * 4;
+    for (uint32_t i = 0; i < interrupt_cells; i++)
+    {
+        cells[i] = fdt32_to_cpu(prop + i * 4);
+    }
+    return (int)interrupt_cells;
+}
+
+int fdt_memory_regions(const fdt_t *fdt, fdt_region_t *regions, int max)
+{
+    int count = 0;
+    int root = fdt_next_node(fdt, -1, NULL);
+    for (int node = fdt_first_subnode(fdt, root); node >= 0 && count < max; node = fdt_next_subnode(fdt, node))
+    {
+        const char *type = fdt_getprop_string(fdt, node, "device_type");
+        if (!type || strcmp(type, "memory") != 0 || !fdt_node_is_enabled(fdt, node))
+        {
+            continue;
+        }
+        for (uint32_t index = 0; count < max; index++)
+        {
+            uint64_t base, size;
+            if (fdt_read_reg(fdt, node, index, &base, &size) != OR_OK)
+            {
+                break;
+            }
+            if (size != 0)
+            {
+                regions[count].base = base;
+                regions[count].size = size;
+                count++;
+            }
+        }
+    }
+    return count;
+}
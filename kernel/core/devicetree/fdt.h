@@ -0,0 +1,112 @@
+/*
+ * Orion Operating System - Flattened Device Tree
+ *
+ * Read-only parser for the device tree blob handed over by firmware on
+ * platforms without ACPI (QEMU virt, most ARM and RISC-V boards). Nodes
+ * are designated by their offset in the structure block, as in libfdt;
+ * negative values mean "not found". Nothing is allocated: properties are
+ * returned as pointers into the blob, whose cells are big-endian.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_FDT_H
+#define ORION_FDT_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define FDT_MAGIC 0xD00DFEED
+#define FDT_MIN_VERSION 16
+
+#define FDT_BEGIN_NODE 0x1
+#define FDT_END_NODE 0x2
+#define FDT_PROP 0x3
+#define FDT_NOP 0x4
+#define FDT_END 0x9
+
+// Nesting followed by fdt_parent_offset
+#define FDT_MAX_DEPTH 32
+
+#define FDT_MAX_MEMORY_REGIONS 8
+
+    typedef struct fdt
+    {
+        const uint8_t *blob;
+        const uint8_t *structs;
+        const char *strings;
+        uint32_t total_size;
+        uint32_t struct_size;
+        uint32_t strings_size;
+        uint32_t boot_cpu;
+    } fdt_t;
+
+    typedef struct fdt_region
+    {
+        uint64_t base;
+        uint64_t size;
+    } fdt_region_t;
+
+    // Check the header of the blob at `blob` and fill `fdt`
+    int fdt_init(fdt_t *fdt, const void *blob);
+
+    // Blob handed over by the boot code, NULL before fdt_set_boot
+    int fdt_set_boot(const void *blob);
+    const fdt_t *fdt_boot(void);
+
+    static inline uint32_t fdt32_to_cpu(const void *p)
+    {
+        const uint8_t *b = (const uint8_t *)p;
+        return ((uint32_t)b[0] << 24) | ((uint32_t)b[1] << 16) | ((uint32_t)b[2] << 8) | b[3];
+    }
+
+    // Value of `cells` consecutive 32-bit cells (1 or 2)
+    uint64_t fdt_read_cells(const void *p, uint32_t cells);
+
+    // Node walking: pass -1 and depth -1 to get the root
+    int fdt_next_node(const fdt_t *fdt, int offset, int *depth);
+    int fdt_first_subnode(const fdt_t *fdt, int parent);
+    int fdt_next_subnode(const fdt_t *fdt, int offset);
+    int fdt_parent_offset(const fdt_t *fdt, int node);
+    int fdt_subnode_offset(const fdt_t *fdt, int parent, const char *name);
+    int fdt_path_offset(const fdt_t *fdt, const char *path);
+    int fdt_node_offset_by_phandle(const fdt_t *fdt, uint32_t phandle);
+    // First node after `start` (-1 for the whole tree) listing `compatible`
+    int fdt_node_offset_by_compatible(const fdt_t *fdt, int start, const char *compatible);
+    const char *fdt_get_name(const fdt_t *fdt, int node);
+
+    // Properties
+    const void *fdt_getprop(const fdt_t *fdt, int node, const char *name, uint32_t *len);
+    bool fdt_getprop_u32(const fdt_t *fdt, int node, const char *name, uint32_t *value);
+    const char *fdt_getprop_string(const fdt_t *fdt, int node, const char *name);
+    bool fdt_node_is_compatible(const fdt_t *fdt, int node, const char *compatible);
+    // False for nodes with status other than "okay"/"ok"
+    bool fdt_node_is_enabled(const fdt_t *fdt, int node);
+
+    // Translate the `index`th entry of "reg" with the parent's cell sizes
+    int fdt_read_reg(const fdt_t *fdt, int node, uint32_t index, uint64_t *addr, uint64_t *size);
+    // Copy the `index`th entry of "interrupts", sized by the interrupt
+    // parent's #interrupt-cells. Returns the number of cells
+    int fdt_read_interrupt(const fdt_t *fdt, int node, uint32_t index, uint32_t *cells, uint32_t max_cells);
+
+    // RAM described by the memory nodes. Returns the number of regions
+    int fdt_memory_regions(const fdt_t *fdt, fdt_region_t *regions, int max);
+
+    struct orion_boot_info;
+    // Boot information for kernel_main on machines booted without the UEFI
+    // loader, its memory map taken from `ram` (fdt_boot.c)
+    struct orion_boot_info *fdt_build_boot_info(const fdt_region_t *ram, int count);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_FDT_H
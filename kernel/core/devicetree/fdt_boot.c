@@ -0,0 +1,61 @@
+/*
+ * Orion Operating System - Device Tree Boot Information
+ *
+ * Machines started by firmware with a device tree (QEMU virt on AArch64
+ * and RISC-V) have no UEFI loader to build the Orion boot information.
+ * The architecture's boot code builds it here instead, so kernel_main
+ * receives the same sealed structure on every platform.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/fdt.h>
+#include "orion-boot-protocol.h"
+
+#define FDT_BOOT_INFO_SIZE 512
+
+static uint8_t g_boot_info_buffer[FDT_BOOT_INFO_SIZE] __attribute__((aligned(8)));
+
+// Describe the device tree memory nodes the way the UEFI loader describes
+// its memory map
+struct orion_boot_info *fdt_build_boot_info(const fdt_region_t *ram, int count)
+{
+    struct orion_boot_info *info = (struct orion_boot_info *)g_boot_info_buffer;
+    struct orion_memory_info *memory = (struct orion_memory_info *)(info + 1);
+    struct orion_memory_entry *entries = (struct orion_memory_entry *)(memory + 1);
+    uint8_t *end = g_boot_info_buffer + FDT_BOOT_INFO_SIZE - sizeof(struct orion_info_tag);
+
+    memset(g_boot_info_buffer, 0, sizeof(g_boot_info_buffer));
+    uint64_t total = 0;
+    uint32_t used = 0;
+    for (int i = 0; i < count && (uint8_t *)&entries[used + 1] <= end; i++, used++)
+    {
+        entries[used].base_addr = ram[i].base;
+        entries[used].length = ram[i].size;
+        entries[used].type = ORION_MEMORY_AVAILABLE;
+        total += ram[i].size;
+    }
+
+    memory->header.type = ORION_INFO_MEMORY;
+    memory->header.size = sizeof(*memory) + used * sizeof(struct orion_memory_entry);
+    memory->total_memory = total;
+    memory->available_memory = total;
+    memory->memory_map_entries = used;
+
+    struct orion_info_tag *terminator = (struct orion_info_tag *)&entries[used];
+    terminator->type = ORION_INFO_END;
+    terminator->size = sizeof(*terminator);
+
+    info->magic = ORION_BOOT_MAGIC;
+    info->version = ORION_BOOT_VERSION;
+    info->info_count = 1;
+    info->total_size = (uint32_t)((uint8_t *)(terminator + 1) - g_boot_info_buffer);
+    orion_boot_info_seal(info);
+    return info;
+}
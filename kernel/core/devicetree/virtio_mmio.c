@@ -0,0 +1,181 @@
+/*
+ * Orion Operating System - Virtio-MMIO Device Discovery
+ *
+ * QEMU's virt machines declare a fixed number of virtio-mmio slots and
+ * fill them from the end; empty slots answer with device id 0 and are
+ * skipped. Interrupt specifiers are translated by the registered
+ * interrupt controller, so discovery must run after it is up.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+#include <orion/fdt.h>
+#include <orion/virtio_mmio.h>
+
+static virtio_mmio_device_t g_virtio_devices[VIRTIO_MMIO_MAX_DEVICES];
+static uint32_t g_virtio_device_count = 0;
+
+// Device types with a transitional virtio-pci id
+static const struct
+{
+    uint32_t device_id;
+    uint16_t pci_device_id;
+    const char *name;
+} g_virtio_types[] = {
+    {1, 0x1000, "net"},
+    {2, 0x1001, "block"},
+    {3, 0x1003, "console"},
+    {4, 0x1005, "entropy"},
+    {5, 0x1002, "balloon"},
+    {8, 0x1004, "scsi"},
+    {9, 0x1009, "9p"},
+    {16, 0, "gpu"},
+    {18, 0, "input"},
+    {19, 0, "vsock"},
+    {26, 0, "fs"},
+};
+
+#define VIRTIO_TYPE_COUNT (sizeof(g_virtio_types) / sizeof(g_virtio_types[0]))
+
+static inline uint32_t virtio_mmio_read(void *regs, uint32_t reg)
+{
+    return *(volatile uint32_t *)((uint8_t *)regs + reg);
+}
+
+static uint16_t virtio_pci_device_id(uint32_t device_id)
+{
+    for (size_t i = 0; i < VIRTIO_TYPE_COUNT; i++)
+    {
+        if (g_virtio_types[i].device_id == device_id && g_virtio_types[i].pci_device_id)
+        {
+            return g_virtio_types[i].pci_device_id;
+        }
+    }
+    return (uint16_t)(VIRTIO_PCI_MODERN_DEVICE_BASE + device_id);
+}
+
+const char *virtio_device_name(uint32_t device_id)
+{
+    for (size_t i = 0; i < VIRTIO_TYPE_COUNT; i++)
+    {
+        if (g_virtio_types[i].device_id == device_id)
+        {
+            return g_virtio_types[i].name;
+        }
+    }
+    return "unknown";
+}
+
+static int virtio_mmio_probe_node(const fdt_t *fdt, int node, virtio_mmio_device_t *device)
+{
+    uint64_t base, size;
+    if (fdt_read_reg(fdt, node, 0, &base, &size) != OR_OK || size < 0x100)
+    {
+        return -OR_EINVAL;
+    }
+
+    void *regs = arch_ioremap(base, size);
+    if (!regs)
+    {
+        return -OR_ENOMEM;
+    }
+    if (virtio_mmio_read(regs, VIRTIO_MMIO_REG_MAGIC) != VIRTIO_MMIO_MAGIC_VALUE)
+    {
+        kwarn("virtio-mmio: bad magic at 0x%llx", (unsigned long long)base);
+        return -OR_ENODEV;
+    }
+
+    uint32_t device_id = virtio_mmio_read(regs, VIRTIO_MMIO_REG_DEVICE_ID);
+    if (device_id == 0)
+    {
+        return -OR_ENOENT; // Empty slot
+    }
+
+    uint32_t cells[4];
+    uint32_t irq = 0, irq_type = IRQ_TYPE_LEVEL_HIGH;
+    int count = fdt_read_interrupt(fdt, node, 0, cells, 4);
+    if (count <= 0 || irq_xlate(cells, (uint32_t)count, &irq, &irq_type) != OR_OK)
+    {
+        kwarn("virtio-mmio: no usable interrupt for 0x%llx", (unsigned long long)base);
+        return -OR_ENODEV;
+    }
+
+    device->base = base;
+    device->size = size;
+    device->regs = regs;
+    device->irq = irq;
+    device->irq_type = irq_type;
+    device->version = virtio_mmio_read(regs, VIRTIO_MMIO_REG_VERSION);
+    device->device_id = device_id;
+    device->vendor_id = virtio_mmio_read(regs, VIRTIO_MMIO_REG_VENDOR_ID);
+    device->pci_vendor_id = VIRTIO_PCI_VENDOR_ID;
+    device->pci_device_id = virtio_pci_device_id(device_id);
+    return OR_OK;
+}
+
+int virtio_mmio_discover(const fdt_t *fdt)
+{
+    if (!fdt)
+    {
+        return -OR_EINVAL;
+    }
+
+    g_virtio_device_count = 0;
+    uint32_t slots = 0;
+    for (int node = fdt_node_offset_by_compatible(fdt, -1, "virtio,mmio"); node >= 0;
+         node = fdt_node_offset_by_compatible(fdt, node, "virtio,mmio"))
+    {
+        if (!fdt_node_is_enabled(fdt, node))
+        {
+            continue;
+        }
+        slots++;
+        if (g_virtio_device_count == VIRTIO_MMIO_MAX_DEVICES)
+        {
+            kwarn("virtio-mmio: more than %u devices, ignoring the rest", VIRTIO_MMIO_MAX_DEVICES);
+            break;
+        }
+
+        virtio_mmio_device_t *device = &g_virtio_devices[g_virtio_device_count];
+        if (virtio_mmio_probe_node(fdt, node, device) != OR_OK)
+        {
+            continue;
+        }
+        g_virtio_device_count++;
+        kinfo("virtio-mmio: %s (type %u, v%u) at 0x%llx, IRQ %u, PCI id %04x:%04x",
+              virtio_device_name(device->device_id), device->device_id, device->version,
+              (unsigned long long)device->base, device->irq, device->pci_vendor_id, device->pci_device_id);
+    }
+
+    kinfo("virtio-mmio: %u devices in %u slots", g_virtio_device_count, slots);
+    return (int)g_virtio_device_count;
+}
+
+uint32_t virtio_mmio_device_count(void)
+{
+    return g_virtio_device_count;
+}
+
+const virtio_mmio_device_t *virtio_mmio_get_device(uint32_t index)
+{
+    return index < g_virtio_device_count ? &g_virtio_devices[index] : NULL;
+}
+
+int virtio_mmio_find(uint32_t device_id, int start)
+{
+    for (int i = start + 1; i < (int)g_virtio_device_count; i++)
+    {
+        if (g_virtio_devices[i].device_id == device_id)
+        {
+            return i;
+        }
+    }
+    return -1;
+}
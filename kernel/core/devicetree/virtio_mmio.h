@@ -0,0 +1,69 @@
+/*
+ * Orion Operating System - Virtio-MMIO Device Discovery
+ *
+ * Finds the "virtio,mmio" transports described by the device tree, reads
+ * their identification registers and keeps a table of the slots holding
+ * a device. Each device is also given the PCI vendor and device ids a
+ * virtio-pci function of the same type would have (transitional ids where
+ * one exists), so drivers probing by PCI id match it on any bus.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_VIRTIO_MMIO_H
+#define ORION_VIRTIO_MMIO_H
+
+#include <orion/types.h>
+#include <orion/fdt.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define VIRTIO_MMIO_MAX_DEVICES 32
+
+#define VIRTIO_MMIO_MAGIC_VALUE 0x74726976 // "virt"
+#define VIRTIO_MMIO_REG_MAGIC 0x000
+#define VIRTIO_MMIO_REG_VERSION 0x004
+#define VIRTIO_MMIO_REG_DEVICE_ID 0x008
+#define VIRTIO_MMIO_REG_VENDOR_ID 0x00C
+
+#define VIRTIO_PCI_VENDOR_ID 0x1AF4
+#define VIRTIO_PCI_MODERN_DEVICE_BASE 0x1040
+
+    typedef struct virtio_mmio_device
+    {
+        uint64_t base;
+        uint64_t size;
+        void *regs; // Mapped registers
+        uint32_t irq;
+        uint32_t irq_type;
+        uint32_t version;   // 1 legacy, 2 modern
+        uint32_t device_id; // Virtio device type (1 net, 2 block, ...)
+        uint32_t vendor_id;
+        uint16_t pci_vendor_id;
+        uint16_t pci_device_id;
+    } virtio_mmio_device_t;
+
+    // Scan the device tree; returns the number of devices found
+    int virtio_mmio_discover(const fdt_t *fdt);
+
+    uint32_t virtio_mmio_device_count(void);
+    const virtio_mmio_device_t *virtio_mmio_get_device(uint32_t index);
+    // First device of a virtio type after `start` (-1 for the first), or -1
+    int virtio_mmio_find(uint32_t device_id, int start);
+    const char *virtio_device_name(uint32_t device_id);
+
+    // Provided by the architecture: map device registers uncached
+    void *arch_ioremap(uint64_t phys, size_t size);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_VIRTIO_MMIO_H
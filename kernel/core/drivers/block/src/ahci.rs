@@ -40,6 +40,12 @@ use orion_driver::{
 };
 use orion_async::{Future, Pin, Poll, Context, Waker, AsyncMutex, AsyncChannel, AsyncRwLock};
 use orion_crypto::{Aes256, ChaCha20Poly1305, Blake3};
+use orion_blockstats::{BlockStatistics, BlockStatsSource, Operation};
+use orion_blkio::{
+    encode_ata_trim, BarrierQueue, Command, DeviceCache, DiscardBatcher, DiscardLimits, DiscardRange, IoOp, IoStatus,
+    Recovery, Request, TimeoutPolicy, Watchdog, REQ_PREFLUSH,
+};
+use orion_sys::clock_get;
 use alloc::{
     vec::Vec, collections::{BTreeMap, VecDeque}, boxed::Box, 
     string::String, sync::Arc
@@ -149,6 +155,40 @@ const SATA_CMD_WRITE_DMA: u8 = 0xCA;
 const SATA_CMD_READ_FPDMA_QUEUED: u8 = 0x60;
 const SATA_CMD_WRITE_FPDMA_QUEUED: u8 = 0x61;
 const SATA_CMD_IDENTIFY_DEVICE: u8 = 0xEC;
+const SATA_CMD_WRITE_DMA_FUA_EXT: u8 = 0x3D;
+const SATA_CMD_FLUSH_CACHE_EXT: u8 = 0xEA;
+const SATA_CMD_DATA_SET_MANAGEMENT: u8 = 0x06;
+const ATA_DSM_TRIM: u8 = 0x01;
+
+// IDENTIFY DEVICE words describing discard and cache support
+const ATA_ID_FUA_WORD: usize = 84; // bit 6: WRITE DMA FUA EXT
+const ATA_ID_WRITE_CACHE_WORD: usize = 85; // bit 5: volatile write cache enabled
+const ATA_ID_DSM_BLOCKS_WORD: usize = 105; // max 512-byte DSM payload blocks
+const ATA_ID_TRIM_WORD: usize = 169; // bit 0: DATA SET MANAGEMENT TRIM
+// Largest TRIM payload sent at once, in 512-byte blocks
+const ATA_TRIM_MAX_BLOCKS: u16 = 8;
+
+// Recovery of timed out and failed commands
+const AHCI_GHC_HR: u32 = 1 << 0;
+const AHCI_PORT_CMD_ST: u32 = 1 << 0;
+const AHCI_PORT_CMD_CR: u32 = 1 << 15;
+const AHCI_PORT_IS_TFES: u32 = 1 << 30;
+const AHCI_SCTL_DET_MASK: u32 = 0xF;
+const AHCI_SCTL_DET_COMRESET: u32 = 0x1;
+const AHCI_SSTS_DET_MASK: u32 = 0xF;
+const AHCI_SSTS_DET_ESTABLISHED: u32 = 0x3;
+const AHCI_COMRESET_NS: u64 = 1_000_000; // DET=1 held for at least 1 ms
+const AHCI_LINK_NS: u64 = 1_000_000_000;
+const AHCI_STOP_NS: u64 = 500_000_000;
+const AHCI_HBA_RESET_NS: u64 = 1_000_000_000;
+// Watchdog ids: port index above the 32 command slots
+const AHCI_TAG_PORT_SHIFT: u32 = 5;
+
+// Task file status and error bits (PxTFD)
+const ATA_STATUS_ERR: u32 = 1 << 0;
+const ATA_ERROR_SHIFT: u32 = 8;
+const ATA_ERROR_UNC: u32 = 1 << 6; // uncorrectable data
+const ATA_ERROR_IDNF: u32 = 1 << 4; // sector not found
 const SATA_CMD_SMART_READ_DATA: u8 = 0xB0;
 const SATA_CMD_SMART_READ_LOG: u8 = 0xB0;
 const SATA_CMD_SMART_EXECUTE_OFFLINE: u8 = 0xB0;
@@ -368,6 +408,12 @@ pub struct AhciFisRegH2D {
 // AHCI DRIVER IMPLEMENTATION
 // ========================================
 
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
 pub struct AhciDriver {
     device: DeviceInfo,
     registers: *mut u8,
@@ -380,6 +426,8 @@ pub struct AhciDriver {
     performance_monitor: Option<PerformanceMonitor>,
     encryption_manager: Option<EncryptionManager>,
     raid_manager: Option<RaidManager>,
+    io_stats: BlockStatistics,
+    watchdog: Watchdog,
 }
 
 struct AhciPort {
@@ -395,6 +443,8 @@ struct AhciPort {
     block_size: u32,
     current_command_slot: u32,
     max_command_slots: u32,
+    discard_batcher: Option<DiscardBatcher>,
+    write_cache: DeviceCache,
 }
 
 enum DeviceType {
@@ -434,7 +484,6 @@ struct PerformanceMonitor {
     write_operations: u64,
     total_bytes_read: u64,
     total_bytes_written: u64,
-    average_latency: u64,
     peak_bandwidth: u64,
 }
 
@@ -498,6 +547,8 @@ impl AhciDriver {
             performance_monitor: None,
             encryption_manager: None,
             raid_manager: None,
+            io_stats: BlockStatistics::new(),
+            watchdog: Watchdog::new(TimeoutPolicy::DEFAULT),
         }
     }
 
@@ -666,6 +717,19 @@ impl AhciDriver {
             DeviceType::Sata
         };
 
+        // Discard limits and cache behaviour from the IDENTIFY words
+        let word = |index: usize| u16::from_le_bytes([device_info[2 * index], device_info[2 * index + 1]]);
+        let write_cache = DeviceCache {
+            volatile: (word(ATA_ID_WRITE_CACHE_WORD) & (1 << 5)) != 0,
+            fua: (word(ATA_ID_FUA_WORD) & (1 << 6)) != 0,
+            // FLUSH CACHE EXT cannot be queued next to NCQ commands
+            queued_flush: false,
+        };
+        let discard_batcher = ((word(ATA_ID_TRIM_WORD) & 1) != 0).then(|| {
+            let payload_blocks = word(ATA_ID_DSM_BLOCKS_WORD).clamp(1, ATA_TRIM_MAX_BLOCKS) as usize;
+            DiscardBatcher::new(DiscardLimits { max_ranges: 64 * payload_blocks, ..DiscardLimits::ATA_TRIM })
+        });
+
         Ok(AhciPort {
             port_number,
             command_list_base,
@@ -679,6 +743,8 @@ impl AhciDriver {
             block_size: 512,
             current_command_slot: 0,
             max_command_slots: max_slots,
+            discard_batcher,
+            write_cache,
         })
     }
 
@@ -856,37 +922,11 @@ impl AhciDriver {
             core::ptr::write_volatile(table_ptr, command_table);
         }
 
-        // Issue command
-        let port_cmd = unsafe {
-            core::ptr::read_volatile(port.port_registers.add(AHCI_PORT_CMD as usize) as *const u32)
-        };
-
-        // Set command running
-        unsafe {
-            core::ptr::write_volatile(
-                port.port_registers.add(AHCI_PORT_CMD as usize) as *mut u32,
-                port_cmd | 0x8000 // Command running
-            );
-        }
-
-        // Wait for completion
-        let mut timeout = 1000000;
-        while timeout > 0 {
-            let port_cmd = unsafe {
-                core::ptr::read_volatile(port.port_registers.add(AHCI_PORT_CMD as usize) as *const u32)
-            };
-            if (port_cmd & 0x8000) == 0 {
-                break;
-            }
-            timeout -= 1;
-        }
-
-        if timeout == 0 {
-            return Err(DriverError::IoError);
-        }
-
-        // Update command slot
-        port.current_command_slot = (port.current_command_slot + 1) % port.max_command_slots;
+        // Issue the slot, then wait for it under the watchdog
+        let slot = port.current_command_slot;
+        port_write(port.port_registers, AHCI_PORT_CI, 1 << slot);
+        port.current_command_slot = (slot + 1) % port.max_command_slots;
+        self.wait_for_slot(port_index, slot).await?;
 
         // Update performance metrics
         if let Some(perf_mon) = &mut self.performance_monitor {
@@ -897,7 +937,7 @@ impl AhciDriver {
         Ok(bytes_read)
     }
 
-    async fn write_blocks_ahci(&mut self, port_index: usize, lba: u64, count: u32, buffer: &[u8]) -> DriverResult<usize> {
+    async fn write_blocks_ahci(&mut self, port_index: usize, lba: u64, count: u32, buffer: &[u8], fua: bool) -> DriverResult<usize> {
         if port_index >= self.ports.len() {
             return Err(DriverError::InvalidParameter);
         }
@@ -934,12 +974,12 @@ impl AhciDriver {
             }; 8],
         };
 
-        // Set up FIS for WRITE DMA EXT
+        // Set up FIS for WRITE DMA EXT, or its FUA variant
         let mut fis = AhciFisRegH2D {
             fis_type: FIS_TYPE_REG_H2D,
             pm_port: 0,
             reserved: 0,
-            command: SATA_CMD_WRITE_DMA_EXT,
+            command: if fua { SATA_CMD_WRITE_DMA_FUA_EXT } else { SATA_CMD_WRITE_DMA_EXT },
             features: 0,
             lba_low: (lba & 0xFF) as u8,
             lba_mid: ((lba >> 8) & 0xFF) as u8,
@@ -977,37 +1017,11 @@ impl AhciDriver {
             core::ptr::write_volatile(table_ptr, command_table);
         }
 
-        // Issue command
-        let port_cmd = unsafe {
-            core::ptr::read_volatile(port.port_registers.add(AHCI_PORT_CMD as usize) as *const u32)
-        };
-
-        // Set command running
-        unsafe {
-            core::ptr::write_volatile(
-                port.port_registers.add(AHCI_PORT_CMD as usize) as *mut u32,
-                port_cmd | 0x8000 // Command running
-            );
-        }
-
-        // Wait for completion
-        let mut timeout = 1000000;
-        while timeout > 0 {
-            let port_cmd = unsafe {
-                core::ptr::read_volatile(port.port_registers.add(AHCI_PORT_CMD as usize) as *const u32)
-            };
-            if (port_cmd & 0x8000) == 0 {
-                break;
-            }
-            timeout -= 1;
-        }
-
-        if timeout == 0 {
-            return Err(DriverError::IoError);
-        }
-
-        // Update command slot
-        port.current_command_slot = (port.current_command_slot + 1) % port.max_command_slots;
+        // Issue the slot, then wait for it under the watchdog
+        let slot = port.current_command_slot;
+        port_write(port.port_registers, AHCI_PORT_CI, 1 << slot);
+        port.current_command_slot = (slot + 1) % port.max_command_slots;
+        self.wait_for_slot(port_index, slot).await?;
 
         // Update performance metrics
         if let Some(perf_mon) = &mut self.performance_monitor {
@@ -1434,6 +1448,361 @@ impl OrionDriver for AhciDriver {
     }
 }
 
+// ========================================
+// DISCARD AND WRITE ORDERING
+// ========================================
+
+/// Data stage of a request going through the barrier queue
+#[derive(Clone, Copy)]
+enum AhciIo<'a> {
+    Write { lba: u64, count: u32, buffer: &'a [u8] },
+    Trim { ranges: &'a [DiscardRange] },
+    Flush,
+}
+
+impl AhciDriver {
+    /// Write `count` blocks honouring REQ_PREFLUSH and REQ_FUA. Pending
+    /// TRIMs of the range are dropped, and the others are sent ahead of a
+    /// preflush so they stay ordered before the barrier.
+    pub async fn write_blocks_ordered(&mut self, port_index: usize, lba: u64, count: u32, buffer: &[u8], flags: u8) -> DriverResult<usize> {
+        if port_index >= self.ports.len() {
+            return Err(DriverError::InvalidParameter);
+        }
+
+        if let Some(batcher) = &mut self.ports[port_index].discard_batcher {
+            batcher.cancel(lba, count as u64);
+        }
+        if (flags & REQ_PREFLUSH) != 0 {
+            self.send_trims(port_index).await?;
+        }
+
+        let start = monotonic_ns();
+        let mut written = 0;
+        let result = self
+            .run_ordered(port_index, Request { op: IoOp::Write, flags, tag: AhciIo::Write { lba, count, buffer } }, &mut written)
+            .await
+            .map(|_| written);
+        self.record_io(Operation::Write, start, &result);
+        result
+    }
+
+    /// Queue a TRIM of `count` sectors at `lba`. TRIMs are sent once a full
+    /// DATA SET MANAGEMENT payload is pending, or by the next flush.
+    pub async fn trim(&mut self, port_index: usize, lba: u64, count: u64) -> DriverResult<()> {
+        if port_index >= self.ports.len() {
+            return Err(DriverError::InvalidParameter);
+        }
+
+        let port = &mut self.ports[port_index];
+        if lba.checked_add(count).map_or(true, |end| end > port.device_capacity) {
+            return Err(DriverError::InvalidParameter);
+        }
+
+        let batcher = port.discard_batcher.as_mut().ok_or(DriverError::Unsupported)?;
+        batcher.add(lba, count);
+        if batcher.is_full() {
+            self.send_trims(port_index).await?;
+        }
+        Ok(())
+    }
+
+    /// Make every completed write on the port durable, after sending
+    /// pending TRIMs
+    pub async fn flush(&mut self, port_index: usize) -> DriverResult<()> {
+        if port_index >= self.ports.len() {
+            return Err(DriverError::InvalidParameter);
+        }
+
+        self.send_trims(port_index).await?;
+        let start = monotonic_ns();
+        let result = self
+            .run_ordered(port_index, Request { op: IoOp::Flush, flags: 0, tag: AhciIo::Flush }, &mut 0)
+            .await
+            .map(|_| 0);
+        self.record_io(Operation::Flush, start, &result);
+        result.map(|_| ())
+    }
+
+    /// Send the pending TRIMs as DATA SET MANAGEMENT commands
+    async fn send_trims(&mut self, port_index: usize) -> DriverResult<()> {
+        let port = &mut self.ports[port_index];
+        let block_size = port.block_size as u64;
+        let commands = match &mut port.discard_batcher {
+            Some(batcher) => batcher.take_commands(),
+            None => return Ok(()),
+        };
+
+        for ranges in commands {
+            let bytes = ranges.iter().map(|range| range.count).sum::<u64>() * block_size;
+            let start = monotonic_ns();
+            let result = self
+                .run_ordered(port_index, Request { op: IoOp::Discard, flags: 0, tag: AhciIo::Trim { ranges: &ranges } }, &mut 0)
+                .await
+                .map(|_| bytes as usize);
+            self.record_io(Operation::Trim, start, &result);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Run one request through the barrier queue. Each command completes
+    /// before the next is issued, which also keeps the non-queued FLUSH
+    /// CACHE EXT clear of NCQ commands, so the queue only adds the flushes
+    /// the request and the cache call for.
+    async fn run_ordered(&mut self, port_index: usize, request: Request<AhciIo<'_>>, written: &mut usize) -> DriverResult<()> {
+        let mut queue = BarrierQueue::new(self.ports[port_index].write_cache);
+        let mut error = DriverError::IoError;
+        queue.submit(request);
+
+        while let Some(command) = queue.next_command() {
+            let (id, result) = match command {
+                Command::Io { id, tag, fua, .. } => (id, self.issue_io(port_index, tag, fua).await),
+                Command::Flush { id } => (id, self.issue_io(port_index, AhciIo::Flush, false).await),
+            };
+            queue.complete(id, result.as_ref().map_or_else(io_status, |_| IoStatus::Ok));
+            match result {
+                Ok(bytes) => *written += bytes,
+                Err(e) => error = e,
+            }
+        }
+
+        match queue.take_completed().first() {
+            Some((_, IoStatus::Ok)) => Ok(()),
+            _ => Err(error),
+        }
+    }
+
+    async fn issue_io(&mut self, port_index: usize, io: AhciIo<'_>, fua: bool) -> DriverResult<usize> {
+        match io {
+            AhciIo::Write { lba, count, buffer } => self.write_blocks_ahci(port_index, lba, count, buffer, fua).await,
+            AhciIo::Trim { ranges } => {
+                let payload = encode_ata_trim(ranges);
+                let blocks = (payload.len() / 512) as u16;
+                self.ata_command(port_index, SATA_CMD_DATA_SET_MANAGEMENT, ATA_DSM_TRIM, blocks, &payload).await.map(|_| 0)
+            }
+            AhciIo::Flush => self.ata_command(port_index, SATA_CMD_FLUSH_CACHE_EXT, 0, 0, &[]).await.map(|_| 0),
+        }
+    }
+
+    /// Issue a command writing at most a small `payload` to the device and
+    /// wait for it
+    async fn ata_command(&mut self, port_index: usize, command: u8, features: u8, sector_count: u16, payload: &[u8]) -> DriverResult<()> {
+        let port = &mut self.ports[port_index];
+        if !port.device_connected {
+            return Err(DriverError::DeviceNotFound);
+        }
+
+        let table_base = port.command_list_base + 0x1000 + (port.current_command_slot * 0x80);
+        let command_header = AhciCommandHeader {
+            flags: AHCI_CMD_FIS_LENGTH as u16 | if payload.is_empty() { 0 } else { AHCI_CMD_WRITE as u16 },
+            prdtl: if payload.is_empty() { 0 } else { 1 },
+            prdbc: 0,
+            ctba: table_base as u32,
+            ctbau: 0,
+            reserved: 0,
+        };
+
+        let mut command_table = AhciCommandTable {
+            cfis: [0; 64],
+            acmd: [0; 16],
+            reserved: [0; 48],
+            prdt: [AhciPrdtEntry {
+                dba: payload.as_ptr() as u32,
+                dbau: 0,
+                reserved: 0,
+                dbc: payload.len().saturating_sub(1) as u32, // 0-based count
+            }; 8],
+        };
+
+        let fis = AhciFisRegH2D {
+            fis_type: FIS_TYPE_REG_H2D,
+            pm_port: 0x80, // Command register update
+            reserved: 0,
+            command,
+            features,
+            lba_low: 0,
+            lba_mid: 0,
+            lba_high: 0,
+            device: 0x40, // LBA mode
+            lba_low_exp: 0,
+            lba_mid_exp: 0,
+            lba_high_exp: 0,
+            features_exp: 0,
+            sector_count: (sector_count & 0xFF) as u8,
+            sector_count_exp: (sector_count >> 8) as u8,
+            reserved2: 0,
+            control: 0,
+            reserved3: [0; 4],
+        };
+
+        command_table.cfis[..20].copy_from_slice(unsafe {
+            core::slice::from_raw_parts(&fis as *const _ as *const u8, 20)
+        });
+
+        unsafe {
+            core::ptr::write_volatile(table_base as *mut AhciCommandTable, command_table);
+            let header_ptr = (port.command_list_base as *mut AhciCommandHeader).add(port.current_command_slot as usize);
+            core::ptr::write_volatile(header_ptr, command_header);
+        }
+
+        // Issue the slot and wait for the device to clear it
+        let slot = port.current_command_slot;
+        port_write(port.port_registers, AHCI_PORT_CI, 1 << slot);
+        port.current_command_slot = (slot + 1) % port.max_command_slots;
+        self.wait_for_slot(port_index, slot).await
+    }
+}
+
+// ========================================
+// COMMAND TIMEOUTS AND RECOVERY
+// ========================================
+
+fn port_read(registers: *mut u8, offset: u32) -> u32 {
+    unsafe { core::ptr::read_volatile(registers.add(offset as usize) as *const u32) }
+}
+
+fn port_write(registers: *mut u8, offset: u32, value: u32) {
+    unsafe { core::ptr::write_volatile(registers.add(offset as usize) as *mut u32, value) }
+}
+
+/// Poll `done` until it holds or `timeout_ns` have passed
+fn wait_until(timeout_ns: u64, mut done: impl FnMut() -> bool) -> bool {
+    let deadline = monotonic_ns().saturating_add(timeout_ns);
+    while !done() {
+        if monotonic_ns() >= deadline {
+            return false;
+        }
+        core::hint::spin_loop();
+    }
+    true
+}
+
+/// Outcome of a command from the task file it left: media errors
+/// (uncorrectable data, sector not found) apart from the rest
+fn task_file_result(tfd: u32) -> DriverResult<()> {
+    if tfd & ATA_STATUS_ERR == 0 {
+        return Ok(());
+    }
+    let error = (tfd >> ATA_ERROR_SHIFT) & 0xFF;
+    if error & (ATA_ERROR_UNC | ATA_ERROR_IDNF) != 0 {
+        Err(DriverError::IoError)
+    } else {
+        Err(DriverError::DeviceError)
+    }
+}
+
+/// How a driver error is reported to the barrier queue
+fn io_status(error: &DriverError) -> IoStatus {
+    match error {
+        DriverError::IoError => IoStatus::MediaError,
+        DriverError::Timeout => IoStatus::Timeout,
+        DriverError::DeviceNotReady => IoStatus::Offline,
+        _ => IoStatus::DeviceError,
+    }
+}
+
+/// Clear PxCMD.ST and wait for the command list engine to stop; the HBA
+/// clears PxCI with it
+fn stop_port(registers: *mut u8) -> DriverResult<()> {
+    let cmd = port_read(registers, AHCI_PORT_CMD);
+    port_write(registers, AHCI_PORT_CMD, cmd & !AHCI_PORT_CMD_ST);
+    if !wait_until(AHCI_STOP_NS, || port_read(registers, AHCI_PORT_CMD) & AHCI_PORT_CMD_CR == 0) {
+        return Err(DriverError::Timeout);
+    }
+    Ok(())
+}
+
+fn start_port(registers: *mut u8) {
+    // Errors latched while the port was stopped would stop it again
+    port_write(registers, AHCI_PORT_SERR, u32::MAX);
+    port_write(registers, AHCI_PORT_IS, u32::MAX);
+    let cmd = port_read(registers, AHCI_PORT_CMD);
+    port_write(registers, AHCI_PORT_CMD, cmd | AHCI_PORT_CMD_ST);
+}
+
+impl AhciDriver {
+    /// Wait for the command in `slot` of a port. AHCI has no per-command
+    /// abort, so past its deadline the port link is reset, which drops it;
+    /// if that does not help the whole HBA is reset, and after that it is
+    /// given up (see orion_blkio::timeout).
+    async fn wait_for_slot(&mut self, port_index: usize, slot: u32) -> DriverResult<()> {
+        let id = (port_index as u64) << AHCI_TAG_PORT_SHIFT | slot as u64;
+        self.watchdog.start(id, monotonic_ns());
+
+        loop {
+            let registers = self.ports[port_index].port_registers;
+            let issued = port_read(registers, AHCI_PORT_CI);
+            let failed = port_read(registers, AHCI_PORT_IS) & AHCI_PORT_IS_TFES != 0;
+            if issued & (1 << slot) == 0 || failed {
+                let tfd = port_read(registers, AHCI_PORT_TFD);
+                if failed {
+                    // The port stops on a task file error until restarted
+                    stop_port(registers)?;
+                    start_port(registers);
+                }
+                if self.watchdog.finish(id) {
+                    return Err(DriverError::Timeout);
+                }
+                return task_file_result(tfd);
+            }
+
+            match self.watchdog.poll(monotonic_ns()) {
+                Some(Recovery::Abort(id)) => {
+                    let port_index = (id >> AHCI_TAG_PORT_SHIFT) as usize;
+                    if self.reset_port(port_index).is_err() {
+                        self.watchdog.abort_failed(id);
+                    }
+                }
+                Some(Recovery::Reset) => {
+                    let reset = self.reset_hba().await;
+                    self.watchdog.reset_done(reset.is_ok());
+                    if reset.is_err() {
+                        self.device_ready = false;
+                    }
+                    return Err(DriverError::Timeout);
+                }
+                Some(Recovery::Offline) => {
+                    self.watchdog.abandon();
+                    self.device_ready = false;
+                    return Err(DriverError::DeviceNotReady);
+                }
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+
+    /// COMRESET the link of a port, dropping every command it had issued,
+    /// and start it again once the device is back
+    fn reset_port(&mut self, port_index: usize) -> DriverResult<()> {
+        let registers = self.ports.get(port_index).ok_or(DriverError::InvalidParameter)?.port_registers;
+        stop_port(registers)?;
+
+        let sctl = port_read(registers, AHCI_PORT_SCTL) & !AHCI_SCTL_DET_MASK;
+        port_write(registers, AHCI_PORT_SCTL, sctl | AHCI_SCTL_DET_COMRESET);
+        wait_until(AHCI_COMRESET_NS, || false);
+        port_write(registers, AHCI_PORT_SCTL, sctl);
+        let linked = wait_until(AHCI_LINK_NS, || {
+            port_read(registers, AHCI_PORT_SSTS) & AHCI_SSTS_DET_MASK == AHCI_SSTS_DET_ESTABLISHED
+        });
+        if !linked {
+            return Err(DriverError::DeviceNotFound);
+        }
+
+        start_port(registers);
+        Ok(())
+    }
+
+    /// Reset the whole HBA and bring its ports up again
+    async fn reset_hba(&mut self) -> DriverResult<()> {
+        self.write_register(AHCI_GHC, AHCI_GHC_HR);
+        if !wait_until(AHCI_HBA_RESET_NS, || self.read_register(AHCI_GHC) & AHCI_GHC_HR == 0) {
+            return Err(DriverError::Timeout);
+        }
+        self.ports.clear();
+        self.initialize_controller().await
+    }
+}
+
 // ========================================
 // BLOCK DRIVER IMPLEMENTATION
 // ========================================
@@ -1445,7 +1814,10 @@ impl BlockDriver for AhciDriver {
         }
 
         // Use first available port for now
-        self.read_blocks_ahci(0, lba, count, buffer).await
+        let start = monotonic_ns();
+        let result = self.read_blocks_ahci(0, lba, count, buffer).await;
+        self.record_io(Operation::Read, start, &result);
+        result
     }
 
     async fn write_blocks(&mut self, lba: u64, count: u32, buffer: &[u8]) -> DriverResult<usize> {
@@ -1454,7 +1826,7 @@ impl BlockDriver for AhciDriver {
         }
 
         // Use first available port for now
-        self.write_blocks_ahci(0, lba, count, buffer).await
+        self.write_blocks_ordered(0, lba, count, buffer, 0).await
     }
 
     async fn get_capacity(&self) -> DriverResult<u64> {
@@ -1474,6 +1846,12 @@ impl BlockDriver for AhciDriver {
     }
 }
 
+impl BlockStatsSource for AhciDriver {
+    fn block_statistics(&self) -> &BlockStatistics {
+        &self.io_stats
+    }
+}
+
 // ========================================
 // MANAGER IMPLEMENTATIONS
 // ========================================
@@ -1609,7 +1987,6 @@ impl PerformanceMonitor {
             write_operations: 0,
             total_bytes_read: 0,
             total_bytes_written: 0,
-            average_latency: 0,
             peak_bandwidth: 0,
         }
     }
@@ -1618,11 +1995,6 @@ impl PerformanceMonitor {
         self.read_operations += 1;
         self.total_bytes_read += bytes as u64;
         
-        // Update average latency
-        if self.read_operations > 0 {
-            self.average_latency = (self.average_latency + latency) / 2;
-        }
-        
         // Update peak bandwidth
         let current_bandwidth = bytes as u64 * 1_000_000_000 / latency.max(1);
         if current_bandwidth > self.peak_bandwidth {
@@ -1634,11 +2006,6 @@ impl PerformanceMonitor {
         self.write_operations += 1;
         self.total_bytes_written += bytes as u64;
         
-        // Update average latency
-        if self.write_operations > 0 {
-            self.average_latency = (self.average_latency + latency) / 2;
-        }
-        
         // Update peak bandwidth
         let current_bandwidth = bytes as u64 * 1_000_000_000 / latency.max(1);
         if current_bandwidth > self.peak_bandwidth {
@@ -1656,13 +2023,11 @@ impl PerformanceMonitor {
              Read Operations: {}\n\
              Write Operations: {}\n\
              Total Data: {} bytes\n\
-             Average Latency: {} ns\n\
              Peak Bandwidth: {} bytes/s",
             total_operations,
             self.read_operations,
             self.write_operations,
             total_bytes,
-            self.average_latency,
             self.peak_bandwidth
         )
     }
@@ -1871,6 +2236,10 @@ impl AhciDriver {
             if port.device_connected {
                 status.push(format!("    Capacity: {} bytes", port.device_capacity));
                 status.push(format!("    Block Size: {} bytes", port.block_size));
+                status.push(format!("    Volatile Write Cache: {}", port.write_cache.volatile));
+                if let Some(batcher) = &port.discard_batcher {
+                    status.push(format!("    Pending TRIM: {} sectors", batcher.pending_blocks()));
+                }
             }
         }
         
@@ -1882,6 +2251,11 @@ impl AhciDriver {
         if let Some(perf) = &self.performance_monitor {
             status.push(perf.get_performance_report());
         }
+
+        let latency = self.io_stats.report().latency;
+        status.push(format!("  Latency: p50 {} ns, p99 {} ns, p99.9 {} ns, max {} ns",
+            latency.p50, latency.p99, latency.p999, latency.max
+        ));
         
         if let Some(raid) = &self.raid_manager {
             status.push(raid.get_raid_info());
@@ -1890,6 +2264,14 @@ impl AhciDriver {
         status.join("\n")
     }
 
+    /// Account a finished read or write started at `start` (monotonic ns)
+    fn record_io(&self, operation: Operation, start: u64, result: &DriverResult<usize>) {
+        match result {
+            Ok(bytes) => self.io_stats.record(operation, *bytes as u64, monotonic_ns().saturating_sub(start)),
+            Err(_) => self.io_stats.record_error(operation),
+        }
+    }
+
     /// Execute a SMART self-test
     pub async fn execute_smart_self_test(&mut self, port_index: usize, test_type: u8) -> DriverResult<()> {
         if port_index >= self.ports.len() {
@@ -2118,6 +2500,21 @@ mod tests {
         assert_eq!(perf.read_operations, 1);
         assert_eq!(perf.total_bytes_read, 1024);
     }
+
+    #[test]
+    fn test_block_statistics() {
+        let driver = AhciDriver::new();
+        driver.record_io(Operation::Read, 0, &Ok(4096));
+        driver.record_io(Operation::Write, 0, &Err(DriverError::IoError));
+
+        let report = driver.block_stats_report();
+        assert_eq!((report.read.operations, report.read.bytes), (1, 4096));
+        assert_eq!((report.write.operations, report.write.errors), (0, 1));
+        assert_eq!(report.latency.count, 1);
+
+        driver.reset_block_stats();
+        assert_eq!(driver.block_stats_report().total_errors(), 0);
+    }
     
     #[test]
     fn test_encryption_manager() {
@@ -31,6 +31,7 @@ extern crate alloc;
 
 use alloc::{
     collections::BTreeMap,
+    format,
     string::{String, ToString},
     vec::Vec,
     vec,
@@ -42,6 +43,13 @@ use orion_driver::{
     OrionDriver, BlockDriver, DeviceInfo, DriverError, DriverResult,
     MessageLoop, ReceivedMessage, IpcInterface, IoRequestType,
 };
+use orion_cap::Capability;
+use orion_blkio::integrity::{Algorithm, Boundary, Checksums, IntegrityStats};
+use orion_health::HealthChecks;
+use orion_proto::rights::CAP_ADMIN;
+use orion_sys::{audit_emit, clock_get};
+use orion_tuning::{Bounds, CompressionPolicy, OptimizationEngine, VolumeCache};
+use orion_storpolicy::{admit, reconcile, Action, Dataset, Device, DeviceClass, Snapshot, StoragePolicy, Violation};
 
 /// LVM Driver - Ultra-Modern Logical Volume Management with Full LVM2 Support
 ///
@@ -82,6 +90,78 @@ pub struct LvmDriver {
     multipath_manager: MultiPathManager,
     /// Migration manager
     migration_manager: MigrationManager,
+    /// Pool and volume ACLs checked on control requests
+    access: AccessControl,
+    /// Capability checks for the administrator override
+    capabilities: Capability,
+    /// Pools that checksum their volume I/O end to end
+    integrity: BTreeMap<String, PoolIntegrity>,
+    /// Checks answered to the health server
+    health: HealthChecks<LvmDriver>,
+    /// Adjusts the read caches of the volumes an administrator bounded
+    tuning: OptimizationEngine,
+    /// Read caches of the tuned volumes
+    caches: BTreeMap<String, TunedCache>,
+    /// Storage policies and the volumes they govern
+    storage: StorageManager,
+}
+
+/// End-to-end checksums of a pool and what they found and cost
+#[derive(Debug, Clone, Copy)]
+pub struct PoolIntegrity {
+    pub algorithm: Algorithm,
+    pub stats: IntegrityStats,
+}
+
+/// Read cache of a tuned volume and when its current interval started
+pub struct TunedCache {
+    pub cache: VolumeCache,
+    pub interval_start: u64,
+}
+
+/// Storage policies, the volumes they govern and what the last
+/// reconciliation could not correct
+pub struct StorageManager {
+    policies: BTreeMap<String, StoragePolicy>,
+    /// Policy governing each volume
+    assigned: BTreeMap<String, String>,
+    /// Snapshots taken of each volume for its policy
+    snapshots: BTreeMap<String, Vec<Snapshot>>,
+    violations: BTreeMap<String, Vec<Violation>>,
+    last_reconcile: u64,
+}
+
+impl StorageManager {
+    pub fn new() -> Self {
+        Self {
+            policies: BTreeMap::new(),
+            assigned: BTreeMap::new(),
+            snapshots: BTreeMap::new(),
+            violations: BTreeMap::new(),
+            last_reconcile: 0,
+        }
+    }
+
+    pub fn policy(&self, name: &str) -> Option<&StoragePolicy> {
+        self.policies.get(name)
+    }
+
+    /// Policy governing a volume
+    pub fn policy_of(&self, volume: &str) -> Option<&StoragePolicy> {
+        self.assigned.get(volume).and_then(|name| self.policies.get(name))
+    }
+
+    /// Volumes governed by a policy
+    pub fn volumes_of(&self, policy: &str) -> usize {
+        self.assigned.values().filter(|name| name.as_str() == policy).count()
+    }
+
+    /// Stop governing a volume; the snapshots already taken stay
+    pub fn release(&mut self, volume: &str) {
+        self.assigned.remove(volume);
+        self.snapshots.remove(volume);
+        self.violations.remove(volume);
+    }
 }
 
 /// Driver state
@@ -115,6 +195,8 @@ pub struct LvmStats {
     pub migration_operations: AtomicU64,
     /// Error count
     pub error_count: AtomicU64,
+    /// Control requests refused by an ACL
+    pub access_denied: AtomicU64,
 }
 
 // ========================================
@@ -140,6 +222,10 @@ pub struct PhysicalVolume {
     pub state: PvState,
     /// Metadata area
     pub metadata_area: MetadataArea,
+    /// Class of the device, for storage policies
+    pub device_class: DeviceClass,
+    /// Node the device is attached to, for storage policies
+    pub node: String,
 }
 
 /// Physical Volume State
@@ -243,6 +329,8 @@ pub struct LogicalVolume {
     pub snapshot_info: Option<SnapshotInfo>,
     /// Thin provisioning info
     pub thin_info: Option<ThinInfo>,
+    /// Key size the volume is encrypted with, 0 when stored in clear
+    pub key_bits: u32,
 }
 
 /// Logical Volume Type
@@ -376,6 +464,10 @@ impl PhysicalVolumeManager {
         self.volumes.get(device_path)
     }
 
+    pub fn get_physical_volume_mut(&mut self, device_path: &str) -> Option<&mut PhysicalVolume> {
+        self.volumes.get_mut(device_path)
+    }
+
     pub fn get_all_volumes(&self) -> Vec<&PhysicalVolume> {
         self.volumes.values().collect()
     }
@@ -450,6 +542,13 @@ impl VolumeGroupManager {
         }
         self.states.insert(name.to_string(), state);
     }
+
+    /// Record a logical volume as allocated from a group
+    pub fn add_logical_volume(&mut self, name: &str, lv_name: String) {
+        if let Some(vg) = self.groups.get_mut(name) {
+            vg.logical_volumes.push(lv_name);
+        }
+    }
 }
 
 /// Logical Volume Manager
@@ -496,6 +595,7 @@ impl LogicalVolumeManager {
             segments: Vec::new(),
             snapshot_info: None,
             thin_info: None,
+            key_bits: 0,
         };
 
         self.volumes.insert(name.clone(), lv);
@@ -512,6 +612,10 @@ impl LogicalVolumeManager {
         self.volumes.get(name)
     }
 
+    pub fn get_logical_volume_mut(&mut self, name: &str) -> Option<&mut LogicalVolume> {
+        self.volumes.get_mut(name)
+    }
+
     pub fn get_all_volumes(&self) -> Vec<&LogicalVolume> {
         self.volumes.values().collect()
     }
@@ -584,6 +688,7 @@ impl SnapshotManager {
                 cow_table_size: size / 10, // 10% of size for COW table
             }),
             thin_info: None,
+            key_bits: 0,
         };
 
         self.snapshots.insert(name.clone(), snapshot);
@@ -1006,6 +1111,32 @@ pub enum MigrationPolicy {
 // UTILITY FUNCTIONS
 // ========================================
 
+/// Node name of the devices attached to this machine
+const LOCAL_NODE: &str = "local";
+
+/// Class of a device guessed from its path, until SET_DEVICE_PLACEMENT
+/// says otherwise
+fn device_class_of(device_path: &str) -> DeviceClass {
+    if device_path.starts_with("/dev/nvme") {
+        DeviceClass::Nvme
+    } else if device_path.starts_with("/dev/nbd") || device_path.starts_with("/dev/iscsi") {
+        DeviceClass::Remote
+    } else {
+        DeviceClass::Hdd
+    }
+}
+
+/// Devices holding a copy of a volume
+fn replicas_of(lv: &LogicalVolume) -> Vec<String> {
+    let mut replicas: Vec<String> = Vec::new();
+    for device in lv.segments.iter().flat_map(|segment| segment.physical_volumes.iter()) {
+        if !replicas.contains(device) {
+            replicas.push(device.clone());
+        }
+    }
+    replicas
+}
+
 /// Generate a simple UUID (for demonstration purposes)
 fn generate_uuid() -> String {
     use core::fmt::Write;
@@ -1035,6 +1166,13 @@ impl LvmDriver {
             encryption_manager: EncryptionManager::new(),
             multipath_manager: MultiPathManager::new(),
             migration_manager: MigrationManager::new(),
+            access: AccessControl::new(),
+            capabilities: Capability::new(),
+            integrity: BTreeMap::new(),
+            health: HealthChecks::new().readiness("driver", driver_ready).readiness("pools", pools_online),
+            tuning: OptimizationEngine::default(),
+            caches: BTreeMap::new(),
+            storage: StorageManager::new(),
         }
     }
 
@@ -1072,6 +1210,8 @@ impl LvmDriver {
                 offset: 0,
                 format_version: 2,
             },
+            device_class: device_class_of(&device_path),
+            node: LOCAL_NODE.to_string(),
         };
 
         self.pv_manager.add_physical_volume(device_path, pv);
@@ -1089,9 +1229,11 @@ impl LvmDriver {
         name: String,
         size: u64,
         lv_type: LvType,
-        _vg_name: String,
+        vg_name: String,
     ) -> DriverResult<()> {
-        self.lv_manager.create_logical_volume(name, size, lv_type, _vg_name)
+        self.lv_manager.create_logical_volume(name.clone(), size, lv_type, vg_name.clone())?;
+        self.vg_manager.add_logical_volume(&vg_name, name);
+        Ok(())
     }
 
     /// Create a snapshot
@@ -1132,6 +1274,8 @@ impl LvmDriver {
     /// Remove a logical volume
     pub fn remove_logical_volume(&mut self, name: &str) -> DriverResult<()> {
         if self.lv_manager.remove_logical_volume(name).is_some() {
+            self.access.remove(ACL_TARGET_VOLUME, name);
+            self.storage.release(name);
             Ok(())
         } else {
             Err(DriverError::DeviceNotFound)
@@ -1141,6 +1285,8 @@ impl LvmDriver {
     /// Remove a volume group
     pub fn remove_volume_group(&mut self, name: &str) -> DriverResult<()> {
         if self.vg_manager.remove_volume_group(name).is_some() {
+            self.access.remove(ACL_TARGET_POOL, name);
+            self.integrity.remove(name);
             Ok(())
         } else {
             Err(DriverError::DeviceNotFound)
@@ -1287,6 +1433,12 @@ impl OrionDriver for LvmDriver {
                         let bytes_written = self.write_blocks(io_msg.offset, 1, &buffer)?;
                         Ok(bytes_written)
                     }
+                    IoRequestType::Ioctl if io_msg.length == LVM_IOCTL_CONTROL => {
+                        // Management requests answer with data rather than a length
+                        let requester = Requester { pid: io_msg.header.sender, capability: io_msg.header.capability };
+                        let reply = self.handle_control(requester, &io_msg.data);
+                        return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                    }
                     IoRequestType::Ioctl => {
                         // Handle LVM-specific ioctl commands
                         self.handle_lvm_ioctl(io_msg.length, io_msg.data)
@@ -1530,6 +1682,1314 @@ impl LvmDriver {
     }
 }
 
+// ========================================
+// MANAGEMENT CONTROL PROTOCOL
+// ========================================
+
+/// Ioctl carrying a control request; the reply is sent back as data
+pub const LVM_IOCTL_CONTROL: u32 = 0x1010;
+
+// Control opcodes
+pub const CTRL_LIST_POOLS: u32 = 1;
+pub const CTRL_LIST_SNAPSHOTS: u32 = 2;
+pub const CTRL_CREATE_SNAPSHOT: u32 = 3;
+pub const CTRL_REMOVE_SNAPSHOT: u32 = 4;
+pub const CTRL_VOLUME_INFO: u32 = 5;
+pub const CTRL_VOLUME_READ: u32 = 6;
+pub const CTRL_VOLUME_WRITE: u32 = 7;
+pub const CTRL_VOLUME_FLUSH: u32 = 8;
+pub const CTRL_GET_ACL: u32 = 9;
+pub const CTRL_SET_ACL: u32 = 10;
+pub const CTRL_SET_INTEGRITY: u32 = 11;
+pub const CTRL_GET_INTEGRITY: u32 = 12;
+pub const CTRL_VOLUME_READ_INTEGRITY: u32 = 13;
+pub const CTRL_VOLUME_WRITE_INTEGRITY: u32 = 14;
+pub const CTRL_SET_TUNING: u32 = 15;
+pub const CTRL_GET_TUNING: u32 = 16;
+pub const CTRL_TUNING_LOG: u32 = 17;
+pub const CTRL_SET_POLICY: u32 = 18;
+pub const CTRL_REMOVE_POLICY: u32 = 19;
+pub const CTRL_LIST_POLICIES: u32 = 20;
+pub const CTRL_CREATE_VOLUME: u32 = 21;
+pub const CTRL_SET_VOLUME_POLICY: u32 = 22;
+pub const CTRL_POLICY_STATUS: u32 = 23;
+pub const CTRL_SET_DEVICE_PLACEMENT: u32 = 24;
+
+/// Block size of volume I/O through the control protocol
+pub const CTRL_VOLUME_BLOCK_SIZE: u64 = 4096;
+
+/// Largest VOLUME_READ or VOLUME_WRITE, bounded by what one IPC message carries
+pub const CTRL_MAX_TRANSFER: u64 = 64 * 1024;
+
+/// Largest read cache SET_TUNING may allow a volume
+pub const TUNING_MAX_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Largest read-ahead window SET_TUNING may allow, in blocks
+pub const TUNING_MAX_READAHEAD: u32 = 256;
+
+/// Interval the engine is fed the figures of a tuned volume over
+const TUNING_INTERVAL_NS: u64 = 1_000_000_000;
+
+/// Interval governed volumes are reconciled with their policies over
+const POLICY_RECONCILE_NS: u64 = 10_000_000_000;
+
+// Control reply status codes
+pub const CTRL_OK: i32 = 0;
+pub const CTRL_ENOENT: i32 = -2;
+pub const CTRL_EIO: i32 = -5;
+pub const CTRL_EACCES: i32 = -13;
+/// REMOVE_POLICY of a policy still governing volumes
+pub const CTRL_EBUSY: i32 = -16;
+pub const CTRL_EEXIST: i32 = -17;
+pub const CTRL_EINVAL: i32 = -22;
+/// CREATE_VOLUME with no devices its policy allows room on
+pub const CTRL_ENOSPC: i32 = -28;
+/// Data that no longer matches its checksums
+pub const CTRL_EBADMSG: i32 = -74;
+/// *_INTEGRITY volume I/O on a pool without checksums
+pub const CTRL_EOPNOTSUPP: i32 = -95;
+/// SET_ACL against a list that changed since it was read
+pub const CTRL_ESTALE: i32 = -116;
+
+/// Little-endian reader over a control request
+struct ControlReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ControlReader<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.data.get(self.offset..self.offset + 4)?;
+        self.offset += 4;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let bytes = self.data.get(self.offset..self.offset + 8)?;
+        self.offset += 8;
+        let mut value = [0u8; 8];
+        value.copy_from_slice(bytes);
+        Some(u64::from_le_bytes(value))
+    }
+
+    /// `len: u32` followed by UTF-8 bytes
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn vg_state_code(state: &VgState) -> u32 {
+    match state {
+        VgState::Active => 0,
+        VgState::Inactive => 1,
+        VgState::Suspended => 2,
+        VgState::Failed => 3,
+    }
+}
+
+fn snapshot_status_code(status: &SnapshotStatus) -> u32 {
+    match status {
+        SnapshotStatus::Active => 0,
+        SnapshotStatus::Inactive => 1,
+        SnapshotStatus::Invalid => 2,
+    }
+}
+
+/// Ready once initialized and until shut down
+fn driver_ready(driver: &LvmDriver) -> Result<(), String> {
+    match driver.state {
+        DriverState::Ready => Ok(()),
+        ref state => Err(format!("driver {:?}", state)),
+    }
+}
+
+/// Ready while every volume group is active
+fn pools_online(driver: &LvmDriver) -> Result<(), String> {
+    let offline: Vec<String> = driver
+        .vg_manager
+        .get_all_groups()
+        .into_iter()
+        .filter(|vg| vg.state != VgState::Active)
+        .map(|vg| format!("{} {:?}", vg.name, vg.state))
+        .collect();
+    if offline.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("pools offline: {}", offline.join(", ")))
+    }
+}
+
+impl LvmDriver {
+    /// Serve a management request (u32 opcode then arguments) and build
+    /// the reply: i32 status followed by the records of the operation.
+    ///
+    /// LIST_POOLS records: name, size u64, free u64, state u32, volumes u32
+    /// LIST_SNAPSHOTS(pool) records: name, origin, size u64, status u32
+    /// CREATE_SNAPSHOT(name, origin, size u64) and REMOVE_SNAPSHOT(name)
+    /// carry no payload. Strings are `len: u32` followed by UTF-8 bytes.
+    ///
+    /// Volume I/O, for servers exporting a volume (the NBD server):
+    /// VOLUME_INFO(name) answers size u64 and block size u32;
+    /// VOLUME_READ(name, offset u64, length u32) the data;
+    /// VOLUME_WRITE(name, offset u64, data...) and VOLUME_FLUSH(name)
+    /// nothing. Offsets and lengths are whole blocks within the volume.
+    ///
+    /// Access control: GET_ACL(target u32, name) answers generation u64,
+    /// count u32 and the entries; SET_ACL(target, name, generation u64,
+    /// count u32, entries...) replaces them and answers the new
+    /// generation. An entry is principal kind u32, id u64, allow u32 and
+    /// deny u32. Every request is checked against the ACLs of its pool and
+    /// volume and refused with EACCES; LIST_POOLS leaves out the pools the
+    /// requester may not read.
+    ///
+    /// Integrity: SET_INTEGRITY(pool, algorithm u32) turns per-block
+    /// checksums on for a pool (0 turns them off, ACL_ADMIN);
+    /// GET_INTEGRITY(pool) answers algorithm u32, blocks verified u64,
+    /// blocks checksummed u64, mismatches u64 per boundary (5), bytes
+    /// hashed u64, hashing ns u64, I/O ns u64 and overhead ppm u64.
+    /// VOLUME_READ_INTEGRITY(name, offset u64, length u32) answers
+    /// algorithm u32, the tags and the data; VOLUME_WRITE_INTEGRITY(name,
+    /// offset u64, algorithm u32, tags, data...) is checked against its
+    /// tags before it goes further and refused with EBADMSG on a mismatch.
+    /// Both need the pool to have checksums on and its algorithm.
+    ///
+    /// Tuning (see orion_tuning): SET_TUNING(name, cache min u64, cache
+    /// max u64, cache step u64, read-ahead min u32, read-ahead max u32,
+    /// compression policy u32, latency max ns u64) gives a volume a read
+    /// cache the engine sizes, reads ahead with and compresses within
+    /// those bounds (ACL_ADMIN); a cache max of 0 turns it off.
+    /// GET_TUNING(name) answers the bounds as set, all 0 for an untuned
+    /// volume, then cache bytes u64, read-ahead blocks u32, compression
+    /// u32, bytes cached u64 and blocks cached u64. TUNING_LOG(after u64)
+    /// answers the sequence number of the oldest decision kept u64, count
+    /// u32 and the decisions after `after` on volumes the requester may
+    /// read: seq u64, time ns u64, volume, knob u32, old u64, new u64,
+    /// reason u32 and measured u64. Decisions are audited as well.
+    ///
+    /// Storage policies (see orion_storpolicy): SET_POLICY(text) adds or
+    /// replaces a policy and REMOVE_POLICY(name) drops one no volume is
+    /// governed by, or answers EBUSY; both need ACL_ADMIN on no particular
+    /// pool. LIST_POLICIES records: name, text, volumes governed u32.
+    /// CREATE_VOLUME(pool, name, size u64, policy, key bits u32) creates a
+    /// volume on the devices of the pool its policy allows, refused with
+    /// ENOSPC when they lack the room and EINVAL when the key is too weak
+    /// (ACL_ADMIN on the pool). SET_VOLUME_POLICY(name, policy) puts an
+    /// existing volume under a policy, or releases it for an empty name.
+    /// POLICY_STATUS(name) answers policy, replicas u32, snapshots taken
+    /// u32, count u32 and what could not be corrected: violation kind u32,
+    /// two figures u64 and the device concerned. SET_DEVICE_PLACEMENT(
+    /// device, class u32, node) tells where a physical volume is. Governed
+    /// volumes are reconciled every POLICY_RECONCILE_NS: replicas added or
+    /// moved, snapshots taken and expired, each action audited.
+    ///
+    /// Health: the CHECK request of the health server (see orion_health)
+    /// answers whether the driver is initialized and its pools active.
+    pub fn handle_control(&mut self, requester: Requester, request: &[u8]) -> Vec<u8> {
+        if let Some(reply) = self.health.serve(self, request) {
+            return reply;
+        }
+
+        self.reconcile_policies(monotonic_ns());
+
+        let mut payload = Vec::new();
+        let status = self.control(&requester, request, &mut payload).unwrap_or(CTRL_EINVAL);
+
+        let mut reply = Vec::with_capacity(4 + payload.len());
+        reply.extend_from_slice(&status.to_le_bytes());
+        if status == CTRL_OK {
+            reply.extend_from_slice(&payload);
+        }
+        reply
+    }
+
+    /// Check that a volume I/O covers whole blocks inside the volume
+    fn check_volume_range(&self, name: &str, offset: u64, length: u64) -> Result<(), i32> {
+        let size = self.lv_manager.get_logical_volume(name).ok_or(CTRL_ENOENT)?.size;
+        let aligned = offset % CTRL_VOLUME_BLOCK_SIZE == 0 && length % CTRL_VOLUME_BLOCK_SIZE == 0;
+        match offset.checked_add(length) {
+            Some(end) if aligned && length > 0 && length <= CTRL_MAX_TRANSFER && end <= size => Ok(()),
+            _ => Err(CTRL_EINVAL),
+        }
+    }
+
+    /// None when the request is malformed
+    fn control(&mut self, requester: &Requester, request: &[u8], out: &mut Vec<u8>) -> Option<i32> {
+        let mut reader = ControlReader { data: request, offset: 0 };
+
+        let opcode = reader.u32()?;
+        match opcode {
+            CTRL_LIST_POOLS => {
+                for vg in self.vg_manager.get_all_groups() {
+                    if self.access.rights(requester, Some(vg.name.as_str()), None) & ACL_READ == 0 {
+                        continue;
+                    }
+                    put_string(out, &vg.name);
+                    out.extend_from_slice(&vg.size.to_le_bytes());
+                    out.extend_from_slice(&vg.free_size.to_le_bytes());
+                    out.extend_from_slice(&vg_state_code(&vg.state).to_le_bytes());
+                    out.extend_from_slice(&(vg.logical_volumes.len() as u32).to_le_bytes());
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_LIST_SNAPSHOTS => {
+                let pool = reader.string()?;
+                if let Err(status) = self.authorize(requester, opcode, Some(pool.as_str()), None, ACL_READ) {
+                    return Some(status);
+                }
+                let vg = match self.vg_manager.get_volume_group(&pool) {
+                    Some(vg) => vg,
+                    None => return Some(CTRL_ENOENT),
+                };
+                for snapshot in self.snapshot_manager.get_all_snapshots() {
+                    let info = match &snapshot.snapshot_info {
+                        Some(info) if vg.logical_volumes.contains(&info.origin) => info,
+                        _ => continue,
+                    };
+                    put_string(out, &snapshot.name);
+                    put_string(out, &info.origin);
+                    out.extend_from_slice(&snapshot.size.to_le_bytes());
+                    out.extend_from_slice(&snapshot_status_code(&info.status).to_le_bytes());
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_CREATE_SNAPSHOT => {
+                let name = reader.string()?;
+                let origin = reader.string()?;
+                let size = reader.u64()?;
+                if name.is_empty() || size == 0 {
+                    return Some(CTRL_EINVAL);
+                }
+                if self.lv_manager.get_logical_volume(&origin).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if let Err(status) = self.authorize_volume(requester, opcode, &origin, ACL_SNAPSHOT) {
+                    return Some(status);
+                }
+                if self.snapshot_manager.get_snapshot(&name).is_some()
+                    || self.lv_manager.get_logical_volume(&name).is_some()
+                {
+                    return Some(CTRL_EEXIST);
+                }
+                Some(match self.create_snapshot(name, origin, size) {
+                    Ok(()) => CTRL_OK,
+                    Err(_) => CTRL_EINVAL,
+                })
+            }
+            CTRL_REMOVE_SNAPSHOT => {
+                let name = reader.string()?;
+                if self.snapshot_manager.get_snapshot(&name).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_SNAPSHOT) {
+                    return Some(status);
+                }
+                self.snapshot_manager.remove_snapshot(&name);
+                self.access.remove(ACL_TARGET_VOLUME, &name);
+                self.tuning.remove(&name);
+                self.caches.remove(&name);
+                Some(CTRL_OK)
+            }
+            CTRL_VOLUME_INFO => {
+                let name = reader.string()?;
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_READ) {
+                    return Some(status);
+                }
+                let size = match self.lv_manager.get_logical_volume(&name) {
+                    Some(lv) => lv.size,
+                    None => return Some(CTRL_ENOENT),
+                };
+                out.extend_from_slice(&size.to_le_bytes());
+                out.extend_from_slice(&(CTRL_VOLUME_BLOCK_SIZE as u32).to_le_bytes());
+                Some(CTRL_OK)
+            }
+            CTRL_VOLUME_READ => {
+                let name = reader.string()?;
+                let offset = reader.u64()?;
+                let length = reader.u32()? as u64;
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_READ) {
+                    return Some(status);
+                }
+                if let Err(status) = self.check_volume_range(&name, offset, length) {
+                    return Some(status);
+                }
+                let mut buffer = vec![0u8; length as usize];
+                Some(match self.read_volume(&name, offset / CTRL_VOLUME_BLOCK_SIZE, &mut buffer) {
+                    Ok(()) => {
+                        out.extend_from_slice(&buffer);
+                        CTRL_OK
+                    }
+                    Err(_) => CTRL_EIO,
+                })
+            }
+            CTRL_VOLUME_WRITE => {
+                let name = reader.string()?;
+                let offset = reader.u64()?;
+                let data = &request[reader.offset..];
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_WRITE) {
+                    return Some(status);
+                }
+                if let Err(status) = self.check_volume_range(&name, offset, data.len() as u64) {
+                    return Some(status);
+                }
+                Some(match self.write_volume(&name, offset / CTRL_VOLUME_BLOCK_SIZE, data) {
+                    Ok(()) => CTRL_OK,
+                    Err(_) => CTRL_EIO,
+                })
+            }
+            CTRL_SET_INTEGRITY => {
+                let pool = reader.string()?;
+                let algorithm = reader.u32()?;
+                if self.vg_manager.get_volume_group(&pool).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if let Err(status) = self.authorize(requester, opcode, Some(pool.as_str()), None, ACL_ADMIN) {
+                    return Some(status);
+                }
+                if algorithm == 0 {
+                    self.integrity.remove(&pool);
+                    return Some(CTRL_OK);
+                }
+                let algorithm = match Algorithm::from_u32(algorithm) {
+                    Some(algorithm) => algorithm,
+                    None => return Some(CTRL_EINVAL),
+                };
+                // Counters start over with a new algorithm
+                if self.integrity.get(&pool).map(|integrity| integrity.algorithm) != Some(algorithm) {
+                    self.integrity.insert(pool, PoolIntegrity { algorithm, stats: IntegrityStats::default() });
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_GET_INTEGRITY => {
+                let pool = reader.string()?;
+                if let Err(status) = self.authorize(requester, opcode, Some(pool.as_str()), None, ACL_READ) {
+                    return Some(status);
+                }
+                if self.vg_manager.get_volume_group(&pool).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                let (algorithm, stats) = match self.integrity.get(&pool) {
+                    Some(integrity) => (integrity.algorithm.as_u32(), integrity.stats),
+                    None => (0, IntegrityStats::default()),
+                };
+                out.extend_from_slice(&algorithm.to_le_bytes());
+                out.extend_from_slice(&stats.verified.to_le_bytes());
+                out.extend_from_slice(&stats.computed.to_le_bytes());
+                for count in stats.mismatches {
+                    out.extend_from_slice(&count.to_le_bytes());
+                }
+                for value in [stats.hashed_bytes, stats.hash_ns, stats.io_ns, stats.overhead_ppm()] {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_VOLUME_READ_INTEGRITY => {
+                let name = reader.string()?;
+                let offset = reader.u64()?;
+                let length = reader.u32()? as u64;
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_READ) {
+                    return Some(status);
+                }
+                if let Err(status) = self.check_volume_range(&name, offset, length) {
+                    return Some(status);
+                }
+                let pool = match self.pool_integrity(&name) {
+                    Some((pool, _)) => pool,
+                    None => return Some(CTRL_EOPNOTSUPP),
+                };
+                let start = monotonic_ns();
+                let mut buffer = vec![0u8; length as usize];
+                let count = (length / CTRL_VOLUME_BLOCK_SIZE) as u32;
+                if self.read_volume(&name, offset / CTRL_VOLUME_BLOCK_SIZE, &mut buffer).is_err() {
+                    return Some(CTRL_EIO);
+                }
+                let integrity = self.integrity.get_mut(&pool)?;
+                let hashing = monotonic_ns();
+                let checksums = Checksums::compute(integrity.algorithm, CTRL_VOLUME_BLOCK_SIZE as usize, &buffer);
+                let end = monotonic_ns();
+                integrity.stats.record_compute(count as usize, buffer.len(), end.saturating_sub(hashing));
+                integrity.stats.record_io(end.saturating_sub(start));
+                out.extend_from_slice(&integrity.algorithm.as_u32().to_le_bytes());
+                out.extend_from_slice(&checksums.encode());
+                out.extend_from_slice(&buffer);
+                Some(CTRL_OK)
+            }
+            CTRL_VOLUME_WRITE_INTEGRITY => {
+                let name = reader.string()?;
+                let offset = reader.u64()?;
+                let algorithm = Algorithm::from_u32(reader.u32()?)?;
+                let rest = &request[reader.offset..];
+                // Each block comes with its tag, tags first
+                let record = algorithm.tag_size() + CTRL_VOLUME_BLOCK_SIZE as usize;
+                if !rest.len().is_multiple_of(record) {
+                    return Some(CTRL_EINVAL);
+                }
+                let count = rest.len() / record;
+                let checksums = Checksums::decode(algorithm, CTRL_VOLUME_BLOCK_SIZE as usize, count, rest)?;
+                let data = &rest[count * algorithm.tag_size()..];
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_WRITE) {
+                    return Some(status);
+                }
+                if let Err(status) = self.check_volume_range(&name, offset, data.len() as u64) {
+                    return Some(status);
+                }
+                let pool = match self.pool_integrity(&name) {
+                    Some((pool, pool_algorithm)) if pool_algorithm == algorithm => pool,
+                    Some(_) => return Some(CTRL_EINVAL),
+                    None => return Some(CTRL_EOPNOTSUPP),
+                };
+                let start = monotonic_ns();
+                let verified = checksums.verify(data);
+                let hashed = monotonic_ns().saturating_sub(start);
+                let stats = &mut self.integrity.get_mut(&pool)?.stats;
+                stats.record_verify(count, data.len(), hashed);
+                if let Err(mismatch) = verified {
+                    stats.record_mismatch(Boundary::Volume, offset / CTRL_VOLUME_BLOCK_SIZE + mismatch.block as u64);
+                    return Some(CTRL_EBADMSG);
+                }
+                let result = self.write_volume(&name, offset / CTRL_VOLUME_BLOCK_SIZE, data);
+                self.integrity.get_mut(&pool)?.stats.record_io(monotonic_ns().saturating_sub(start));
+                Some(match result {
+                    Ok(_) => CTRL_OK,
+                    Err(_) => CTRL_EIO,
+                })
+            }
+            CTRL_VOLUME_FLUSH => {
+                let name = reader.string()?;
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_WRITE) {
+                    return Some(status);
+                }
+                // Writes reach the physical volumes before they complete
+                Some(match self.lv_manager.get_logical_volume(&name) {
+                    Some(_) => CTRL_OK,
+                    None => CTRL_ENOENT,
+                })
+            }
+            CTRL_GET_ACL => {
+                let target = reader.u32()?;
+                let name = reader.string()?;
+                if let Err(status) = self.authorize_target(requester, opcode, target, &name) {
+                    return Some(status);
+                }
+                let acl = self.access.get(target, &name).cloned().unwrap_or_default();
+                out.extend_from_slice(&acl.generation.to_le_bytes());
+                out.extend_from_slice(&(acl.entries.len() as u32).to_le_bytes());
+                for entry in &acl.entries {
+                    entry.write(out);
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_SET_ACL => {
+                let target = reader.u32()?;
+                let name = reader.string()?;
+                let generation = reader.u64()?;
+                let count = reader.u32()? as usize;
+                if count > ACL_MAX_ENTRIES {
+                    return Some(CTRL_EINVAL);
+                }
+                // The whole list is parsed before anything changes
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    entries.push(AclEntry::read(&mut reader)?);
+                }
+                if let Err(status) = self.authorize_target(requester, opcode, target, &name) {
+                    return Some(status);
+                }
+                Some(match self.access.replace(target, &name, generation, entries) {
+                    Ok(generation) => {
+                        let record = format!(
+                            "storage-acl target={} name={} generation={} entries={} pid={} cap={:#x}",
+                            target_name(target),
+                            name,
+                            generation,
+                            count,
+                            requester.pid,
+                            requester.capability,
+                        );
+                        let _ = audit_emit(AUDIT_STORAGE_ACL, record.as_bytes());
+                        out.extend_from_slice(&generation.to_le_bytes());
+                        CTRL_OK
+                    }
+                    Err(status) => status,
+                })
+            }
+            CTRL_SET_TUNING => {
+                let name = reader.string()?;
+                let (cache_min, cache_max, cache_step) = (reader.u64()?, reader.u64()?, reader.u64()?);
+                let (readahead_min, readahead_max) = (reader.u32()?, reader.u32()?);
+                let compression = CompressionPolicy::from_u32(reader.u32()?)?;
+                let latency_max_ns = reader.u64()?;
+                if self.lv_manager.get_logical_volume(&name).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_ADMIN) {
+                    return Some(status);
+                }
+                let record = format!(
+                    "storage-tuning-bounds volume={} cache={}..{}/{} readahead={}..{} compression={} latency_max={} pid={} cap={:#x}",
+                    name,
+                    cache_min,
+                    cache_max,
+                    cache_step,
+                    readahead_min,
+                    readahead_max,
+                    compression.as_u32(),
+                    latency_max_ns,
+                    requester.pid,
+                    requester.capability,
+                );
+                if cache_max == 0 {
+                    self.tuning.remove(&name);
+                    self.caches.remove(&name);
+                    let _ = audit_emit(AUDIT_STORAGE_TUNING, record.as_bytes());
+                    return Some(CTRL_OK);
+                }
+                if cache_max > TUNING_MAX_CACHE_BYTES || readahead_max > TUNING_MAX_READAHEAD {
+                    return Some(CTRL_EINVAL);
+                }
+                let bounds =
+                    Bounds { cache_min, cache_max, cache_step, readahead_min, readahead_max, compression, latency_max_ns };
+                let now = monotonic_ns();
+                let last = self.tuning.log().last_seq();
+                let settings = match self.tuning.set_bounds(&name, bounds, now) {
+                    Some(settings) => settings,
+                    None => return Some(CTRL_EINVAL),
+                };
+                let _ = audit_emit(AUDIT_STORAGE_TUNING, record.as_bytes());
+                self.audit_decisions(last);
+                self.caches
+                    .entry(name)
+                    .or_insert_with(|| TunedCache {
+                        cache: VolumeCache::new(CTRL_VOLUME_BLOCK_SIZE as usize, settings),
+                        interval_start: now,
+                    })
+                    .cache
+                    .apply(settings);
+                Some(CTRL_OK)
+            }
+            CTRL_GET_TUNING => {
+                let name = reader.string()?;
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_READ) {
+                    return Some(status);
+                }
+                if self.lv_manager.get_logical_volume(&name).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                let tuned = self.caches.get(&name);
+                let bounds = self.tuning.bounds(&name).unwrap_or(Bounds {
+                    cache_min: 0,
+                    cache_max: 0,
+                    cache_step: 0,
+                    readahead_min: 0,
+                    readahead_max: 0,
+                    compression: CompressionPolicy::Auto,
+                    latency_max_ns: 0,
+                });
+                for value in [bounds.cache_min, bounds.cache_max, bounds.cache_step] {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+                for value in [bounds.readahead_min, bounds.readahead_max, bounds.compression.as_u32()] {
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+                out.extend_from_slice(&bounds.latency_max_ns.to_le_bytes());
+                let settings = tuned.map(|tuned| tuned.cache.settings()).unwrap_or_default();
+                out.extend_from_slice(&settings.cache_bytes.to_le_bytes());
+                out.extend_from_slice(&settings.readahead_blocks.to_le_bytes());
+                out.extend_from_slice(&(settings.compression as u32).to_le_bytes());
+                let (stored, blocks) =
+                    tuned.map_or((0, 0), |tuned| (tuned.cache.stored_bytes(), tuned.cache.cached_blocks() as u64));
+                out.extend_from_slice(&stored.to_le_bytes());
+                out.extend_from_slice(&blocks.to_le_bytes());
+                Some(CTRL_OK)
+            }
+            CTRL_TUNING_LOG => {
+                let after = reader.u64()?;
+                let log = self.tuning.log();
+                let decisions: Vec<_> = log
+                    .since(after)
+                    .filter(|decision| {
+                        let pool = self.pool_of(&decision.volume);
+                        self.access.rights(requester, pool, Some(decision.volume.as_str())) & ACL_READ != 0
+                    })
+                    .collect();
+                out.extend_from_slice(&log.first_seq().to_le_bytes());
+                out.extend_from_slice(&(decisions.len() as u32).to_le_bytes());
+                for decision in decisions {
+                    out.extend_from_slice(&decision.seq.to_le_bytes());
+                    out.extend_from_slice(&decision.time_ns.to_le_bytes());
+                    put_string(out, &decision.volume);
+                    out.extend_from_slice(&decision.knob.as_u32().to_le_bytes());
+                    out.extend_from_slice(&decision.old.to_le_bytes());
+                    out.extend_from_slice(&decision.new.to_le_bytes());
+                    out.extend_from_slice(&decision.reason.as_u32().to_le_bytes());
+                    out.extend_from_slice(&decision.reason.measured().to_le_bytes());
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_SET_POLICY => {
+                let text = reader.string()?;
+                if let Err(status) = self.authorize(requester, opcode, None, None, ACL_ADMIN) {
+                    return Some(status);
+                }
+                let policy = match StoragePolicy::parse(&text) {
+                    Ok(policy) => policy,
+                    Err(_) => return Some(CTRL_EINVAL),
+                };
+                let record = format!("storage-policy op=set policy={} pid={}", policy.name, requester.pid);
+                let _ = audit_emit(AUDIT_STORAGE_POLICY, record.as_bytes());
+                self.storage.policies.insert(policy.name.clone(), policy);
+                // The volumes it governs follow the new rules at the next pass
+                self.storage.last_reconcile = 0;
+                Some(CTRL_OK)
+            }
+            CTRL_REMOVE_POLICY => {
+                let name = reader.string()?;
+                if let Err(status) = self.authorize(requester, opcode, None, None, ACL_ADMIN) {
+                    return Some(status);
+                }
+                if self.storage.policy(&name).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if self.storage.volumes_of(&name) > 0 {
+                    return Some(CTRL_EBUSY);
+                }
+                self.storage.policies.remove(&name);
+                let record = format!("storage-policy op=remove policy={} pid={}", name, requester.pid);
+                let _ = audit_emit(AUDIT_STORAGE_POLICY, record.as_bytes());
+                Some(CTRL_OK)
+            }
+            CTRL_LIST_POLICIES => {
+                if let Err(status) = self.authorize(requester, opcode, None, None, ACL_READ) {
+                    return Some(status);
+                }
+                for policy in self.storage.policies.values() {
+                    put_string(out, &policy.name);
+                    put_string(out, &policy.to_text());
+                    out.extend_from_slice(&(self.storage.volumes_of(&policy.name) as u32).to_le_bytes());
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_CREATE_VOLUME => {
+                let pool = reader.string()?;
+                let name = reader.string()?;
+                let size = reader.u64()?;
+                let policy = reader.string()?;
+                let key_bits = reader.u32()?;
+                if name.is_empty() || size == 0 || size % CTRL_VOLUME_BLOCK_SIZE != 0 {
+                    return Some(CTRL_EINVAL);
+                }
+                if self.vg_manager.get_volume_group(&pool).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if let Err(status) = self.authorize(requester, opcode, Some(pool.as_str()), None, ACL_ADMIN) {
+                    return Some(status);
+                }
+                if self.snapshot_manager.get_snapshot(&name).is_some()
+                    || self.lv_manager.get_logical_volume(&name).is_some()
+                {
+                    return Some(CTRL_EEXIST);
+                }
+                Some(match self.create_policy_volume(&pool, &name, size, &policy, key_bits) {
+                    Ok(()) => CTRL_OK,
+                    Err(status) => status,
+                })
+            }
+            CTRL_SET_VOLUME_POLICY => {
+                let name = reader.string()?;
+                let policy = reader.string()?;
+                if self.lv_manager.get_logical_volume(&name).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_ADMIN) {
+                    return Some(status);
+                }
+                if !policy.is_empty() && self.storage.policy(&policy).is_none() {
+                    return Some(CTRL_ENOENT);
+                }
+                let record = format!(
+                    "storage-policy op=assign volume={} policy={} pid={}",
+                    name,
+                    if policy.is_empty() { "-" } else { policy.as_str() },
+                    requester.pid,
+                );
+                let _ = audit_emit(AUDIT_STORAGE_POLICY, record.as_bytes());
+                if policy.is_empty() {
+                    self.storage.release(&name);
+                } else {
+                    self.storage.assigned.insert(name.clone(), policy);
+                    self.reconcile_volume(&name, monotonic_ns());
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_POLICY_STATUS => {
+                let name = reader.string()?;
+                if let Err(status) = self.authorize_volume(requester, opcode, &name, ACL_READ) {
+                    return Some(status);
+                }
+                let replicas = match self.lv_manager.get_logical_volume(&name) {
+                    Some(lv) => replicas_of(lv).len() as u32,
+                    None => return Some(CTRL_ENOENT),
+                };
+                put_string(out, self.storage.assigned.get(&name).map_or("", String::as_str));
+                out.extend_from_slice(&replicas.to_le_bytes());
+                let taken = self.storage.snapshots.get(&name).map_or(0, Vec::len) as u32;
+                out.extend_from_slice(&taken.to_le_bytes());
+                let violations = self.storage.violations.get(&name).map_or(&[][..], Vec::as_slice);
+                out.extend_from_slice(&(violations.len() as u32).to_le_bytes());
+                for violation in violations {
+                    let (first, second, device) = match violation {
+                        Violation::NoPlacement { placed, needed } | Violation::Degraded { healthy: placed, needed } => {
+                            (*placed as u64, *needed as u64, "")
+                        }
+                        Violation::Misplaced { device } => (0, 0, device.as_str()),
+                        Violation::Unencrypted { key_bits, required } => (*key_bits as u64, *required as u64, ""),
+                    };
+                    out.extend_from_slice(&violation.as_u32().to_le_bytes());
+                    out.extend_from_slice(&first.to_le_bytes());
+                    out.extend_from_slice(&second.to_le_bytes());
+                    put_string(out, device);
+                }
+                Some(CTRL_OK)
+            }
+            CTRL_SET_DEVICE_PLACEMENT => {
+                let device = reader.string()?;
+                let class = DeviceClass::from_u32(reader.u32()?)?;
+                let node = reader.string()?;
+                if node.is_empty() {
+                    return Some(CTRL_EINVAL);
+                }
+                if let Err(status) = self.authorize(requester, opcode, None, None, ACL_ADMIN) {
+                    return Some(status);
+                }
+                let pv = match self.pv_manager.get_physical_volume_mut(&device) {
+                    Some(pv) => pv,
+                    None => return Some(CTRL_ENOENT),
+                };
+                pv.device_class = class;
+                pv.node = node;
+                let record = format!(
+                    "storage-policy op=place device={} class={} node={} pid={}",
+                    device,
+                    class.name(),
+                    pv.node,
+                    requester.pid,
+                );
+                let _ = audit_emit(AUDIT_STORAGE_POLICY, record.as_bytes());
+                self.storage.last_reconcile = 0;
+                Some(CTRL_OK)
+            }
+            _ => None,
+        }
+    }
+
+    /// Read volume blocks, through the volume's cache when it is tuned
+    fn read_volume(&mut self, name: &str, first: u64, buffer: &mut [u8]) -> DriverResult<()> {
+        let Some(mut tuned) = self.caches.remove(name) else {
+            let count = (buffer.len() as u64 / CTRL_VOLUME_BLOCK_SIZE) as u32;
+            return self.read_blocks(first, count, buffer).map(|_| ());
+        };
+        let end = self.lv_manager.get_logical_volume(name).map_or(0, |lv| lv.size / CTRL_VOLUME_BLOCK_SIZE);
+        let start = monotonic_ns();
+        let result = tuned.cache.read(first, buffer, end, |lba, data| {
+            self.read_blocks(lba, (data.len() as u64 / CTRL_VOLUME_BLOCK_SIZE) as u32, data).map(|_| ())
+        });
+        let now = monotonic_ns();
+        tuned.cache.record_read_time(now.saturating_sub(start));
+        self.tune(name, &mut tuned, now);
+        self.caches.insert(name.to_string(), tuned);
+        result
+    }
+
+    /// Write volume blocks, keeping the volume's cache up to date
+    fn write_volume(&mut self, name: &str, first: u64, data: &[u8]) -> DriverResult<()> {
+        self.write_blocks(first, (data.len() as u64 / CTRL_VOLUME_BLOCK_SIZE) as u32, data)?;
+        if let Some(tuned) = self.caches.get_mut(name) {
+            tuned.cache.write(first, data);
+        }
+        Ok(())
+    }
+
+    /// Feed the engine the figures of a tuned volume once its interval is
+    /// over, and apply what it answers
+    fn tune(&mut self, name: &str, tuned: &mut TunedCache, now: u64) {
+        if now.saturating_sub(tuned.interval_start) < TUNING_INTERVAL_NS {
+            return;
+        }
+        tuned.interval_start = now;
+        let sample = tuned.cache.take_sample();
+        let last = self.tuning.log().last_seq();
+        if let Some(settings) = self.tuning.observe(name, &sample, now) {
+            tuned.cache.apply(settings);
+        }
+        self.audit_decisions(last);
+    }
+
+    /// Audit the tuning decisions made after `after`
+    fn audit_decisions(&self, after: u64) {
+        for decision in self.tuning.log().since(after) {
+            let record = format!(
+                "storage-tuning volume={} knob={} old={} new={} reason={} measured={}",
+                decision.volume,
+                decision.knob.name(),
+                decision.old,
+                decision.new,
+                decision.reason.name(),
+                decision.reason.measured(),
+            );
+            let _ = audit_emit(AUDIT_STORAGE_TUNING, record.as_bytes());
+        }
+    }
+
+    /// Devices of a pool as storage policies see them
+    fn policy_devices(&self, pool: &str) -> Vec<Device> {
+        let Some(vg) = self.vg_manager.get_volume_group(pool) else {
+            return Vec::new();
+        };
+        vg.physical_volumes
+            .iter()
+            .filter_map(|path| self.pv_manager.get_physical_volume(path))
+            .map(|pv| Device {
+                name: pv.device_path.clone(),
+                class: pv.device_class,
+                node: pv.node.clone(),
+                free: pv.free_pe_count as u64 * pv.pe_size as u64,
+                online: !matches!(pv.state, PvState::Failed | PvState::Removed),
+            })
+            .collect()
+    }
+
+    /// Create a volume on the devices its policy places it on, refused
+    /// when no placement or key meets the policy
+    fn create_policy_volume(
+        &mut self,
+        pool: &str,
+        name: &str,
+        size: u64,
+        policy: &str,
+        key_bits: u32,
+    ) -> Result<(), i32> {
+        let rules = self.storage.policy(policy).ok_or(CTRL_ENOENT)?;
+        let placement = match admit(rules, &self.policy_devices(pool), size, key_bits) {
+            Ok(placement) => placement,
+            Err(Violation::Unencrypted { .. }) => return Err(CTRL_EINVAL),
+            Err(_) => return Err(CTRL_ENOSPC),
+        };
+        self.create_logical_volume(name.to_string(), size, LvType::Linear, pool.to_string())
+            .map_err(|_| CTRL_EINVAL)?;
+        if let Some(lv) = self.lv_manager.get_logical_volume_mut(name) {
+            lv.key_bits = key_bits;
+        }
+        for device in &placement {
+            self.add_replica(name, device);
+        }
+        self.storage.assigned.insert(name.to_string(), policy.to_string());
+
+        let record = format!("storage-policy op=create volume={} policy={} replicas={}", name, policy, placement.join(","));
+        let _ = audit_emit(AUDIT_STORAGE_POLICY, record.as_bytes());
+        self.reconcile_volume(name, monotonic_ns());
+        Ok(())
+    }
+
+    /// Reconcile every governed volume with its policy once the interval
+    /// is over
+    fn reconcile_policies(&mut self, now: u64) {
+        if self.storage.assigned.is_empty() || now.saturating_sub(self.storage.last_reconcile) < POLICY_RECONCILE_NS {
+            return;
+        }
+        self.storage.last_reconcile = now;
+        let volumes: Vec<String> = self.storage.assigned.keys().cloned().collect();
+        for volume in volumes {
+            self.reconcile_volume(&volume, now);
+        }
+    }
+
+    /// Correct the drift of a volume from its policy and keep what cannot
+    /// be corrected for POLICY_STATUS
+    fn reconcile_volume(&mut self, volume: &str, now: u64) {
+        let (Some(policy), Some(pool), Some(lv)) = (
+            self.storage.policy_of(volume).cloned(),
+            self.pool_of(volume).map(String::from),
+            self.lv_manager.get_logical_volume(volume),
+        ) else {
+            return;
+        };
+        let dataset = Dataset {
+            name: volume.to_string(),
+            size: lv.size,
+            replicas: replicas_of(lv),
+            key_bits: lv.key_bits,
+            snapshots: self.storage.snapshots.get(volume).cloned().unwrap_or_default(),
+        };
+        let result = reconcile(&policy, &dataset, &self.policy_devices(&pool), now);
+        for action in &result.actions {
+            self.apply_policy_action(volume, &policy.name, action, now);
+        }
+        if result.violations.is_empty() {
+            self.storage.violations.remove(volume);
+        } else {
+            self.storage.violations.insert(volume.to_string(), result.violations);
+        }
+    }
+
+    fn apply_policy_action(&mut self, volume: &str, policy: &str, action: &Action, now: u64) {
+        let detail = match action {
+            Action::AddReplica { device } => {
+                if !self.add_replica(volume, device) {
+                    return;
+                }
+                format!("add-replica device={}", device)
+            }
+            Action::RemoveReplica { device } => {
+                self.remove_replica(volume, device);
+                format!("remove-replica device={}", device)
+            }
+            Action::TakeSnapshot => {
+                let name = format!("{}@{}", volume, now / 1_000_000_000);
+                let size = self.lv_manager.get_logical_volume(volume).map_or(0, |lv| lv.size);
+                if self.snapshot_manager.get_snapshot(&name).is_some()
+                    || self.create_snapshot(name.clone(), volume.to_string(), size).is_err()
+                {
+                    return;
+                }
+                let taken = Snapshot { name: name.clone(), created_ns: now };
+                self.storage.snapshots.entry(volume.to_string()).or_default().push(taken);
+                format!("snapshot snapshot={}", name)
+            }
+            Action::ExpireSnapshot { name } => {
+                self.snapshot_manager.remove_snapshot(name);
+                self.access.remove(ACL_TARGET_VOLUME, name);
+                self.tuning.remove(name);
+                self.caches.remove(name);
+                if let Some(taken) = self.storage.snapshots.get_mut(volume) {
+                    taken.retain(|snapshot| &snapshot.name != name);
+                }
+                format!("expire snapshot={}", name)
+            }
+        };
+        let record = format!("storage-policy op={} volume={} policy={}", detail, volume, policy);
+        let _ = audit_emit(AUDIT_STORAGE_POLICY, record.as_bytes());
+    }
+
+    /// Place one more copy of a volume on a device, taking its extents
+    fn add_replica(&mut self, volume: &str, device: &str) -> bool {
+        let (Some(lv), Some(pv)) =
+            (self.lv_manager.get_logical_volume_mut(volume), self.pv_manager.get_physical_volume_mut(device))
+        else {
+            return false;
+        };
+        let extents = lv.size.div_ceil(pv.pe_size as u64) as u32;
+        if pv.free_pe_count < extents {
+            return false;
+        }
+        let first = pv.pe_count - pv.free_pe_count;
+        pv.free_pe_count -= extents;
+
+        if lv.segments.is_empty() {
+            lv.segments.push(LvSegment {
+                segment_type: "linear".to_string(),
+                start_le: 0,
+                le_count: lv.le_count,
+                physical_volumes: Vec::new(),
+                physical_extents: Vec::new(),
+                stripe_count: 1,
+                stripe_size: 0,
+            });
+        }
+        let segment = &mut lv.segments[0];
+        segment.physical_volumes.push(device.to_string());
+        segment.physical_extents.push(first);
+        if segment.physical_volumes.len() > 1 {
+            segment.segment_type = "mirror".to_string();
+            lv.lv_type = LvType::Mirrored;
+        }
+        true
+    }
+
+    /// Drop the copy of a volume on a device, giving its extents back when
+    /// the device is still there
+    fn remove_replica(&mut self, volume: &str, device: &str) {
+        let Some(lv) = self.lv_manager.get_logical_volume_mut(volume) else {
+            return;
+        };
+        let size = lv.size;
+        for segment in &mut lv.segments {
+            while let Some(index) = segment.physical_volumes.iter().position(|pv| pv == device) {
+                segment.physical_volumes.remove(index);
+                segment.physical_extents.remove(index);
+            }
+            if segment.physical_volumes.len() <= 1 {
+                segment.segment_type = "linear".to_string();
+            }
+        }
+        if lv.segments.iter().all(|segment| segment.physical_volumes.len() <= 1) {
+            lv.lv_type = LvType::Linear;
+        }
+        if let Some(pv) = self.pv_manager.get_physical_volume_mut(device) {
+            pv.free_pe_count = (pv.free_pe_count + size.div_ceil(pv.pe_size as u64) as u32).min(pv.pe_count);
+        }
+    }
+
+    /// Pool of a volume and its checksum algorithm, when the pool has them on
+    fn pool_integrity(&self, volume: &str) -> Option<(String, Algorithm)> {
+        let pool = self.pool_of(volume)?;
+        self.integrity.get(pool).map(|integrity| (pool.to_string(), integrity.algorithm))
+    }
+
+    /// Pool holding a volume; snapshots belong to the pool of their origin
+    fn pool_of(&self, volume: &str) -> Option<&str> {
+        let base = self.snapshot_manager.origins.get(volume).map(String::as_str).unwrap_or(volume);
+        self.vg_manager
+            .get_all_groups()
+            .into_iter()
+            .find(|vg| vg.logical_volumes.iter().any(|lv| lv == base))
+            .map(|vg| vg.name.as_str())
+    }
+
+    /// Check that `requester` holds every right in `needed` on a pool or a
+    /// volume, auditing the refusal. Holders of a CAP_ADMIN capability
+    /// always have ACL_ADMIN, so a bad ACL can be repaired
+    fn authorize(
+        &self,
+        requester: &Requester,
+        opcode: u32,
+        pool: Option<&str>,
+        volume: Option<&str>,
+        needed: u32,
+    ) -> Result<(), i32> {
+        let mut rights = self.access.rights(requester, pool, volume);
+        if needed & ACL_ADMIN != 0 && self.capabilities.check_rights(requester.capability, CAP_ADMIN, requester.pid) {
+            rights |= ACL_ADMIN;
+        }
+        if rights & needed == needed {
+            return Ok(());
+        }
+
+        self.stats.access_denied.fetch_add(1, Ordering::Relaxed);
+        let record = format!(
+            "storage-denied op={} pool={} volume={} needed={:#x} held={:#x} pid={} cap={:#x}",
+            opcode,
+            pool.unwrap_or("-"),
+            volume.unwrap_or("-"),
+            needed,
+            rights,
+            requester.pid,
+            requester.capability,
+        );
+        let _ = audit_emit(AUDIT_STORAGE_DENIED, record.as_bytes());
+        Err(CTRL_EACCES)
+    }
+
+    fn authorize_volume(&self, requester: &Requester, opcode: u32, volume: &str, needed: u32) -> Result<(), i32> {
+        self.authorize(requester, opcode, self.pool_of(volume), Some(volume), needed)
+    }
+
+    /// ACL_ADMIN on an existing pool or volume, for GET_ACL and SET_ACL
+    fn authorize_target(&self, requester: &Requester, opcode: u32, target: u32, name: &str) -> Result<(), i32> {
+        match target {
+            ACL_TARGET_POOL if self.vg_manager.get_volume_group(name).is_some() => {
+                self.authorize(requester, opcode, Some(name), None, ACL_ADMIN)
+            }
+            ACL_TARGET_VOLUME
+                if self.lv_manager.get_logical_volume(name).is_some()
+                    || self.snapshot_manager.get_snapshot(name).is_some() =>
+            {
+                self.authorize_volume(requester, opcode, name, ACL_ADMIN)
+            }
+            ACL_TARGET_POOL | ACL_TARGET_VOLUME => Err(CTRL_ENOENT),
+            _ => Err(CTRL_EINVAL),
+        }
+    }
+}
+
+// ========================================
+// ACCESS CONTROL
+// ========================================
+
+// Rights an ACL entry allows or denies
+pub const ACL_READ: u32 = 1 << 0; // LIST_*, VOLUME_INFO, VOLUME_READ*, GET_INTEGRITY, GET_TUNING, POLICY_STATUS
+pub const ACL_WRITE: u32 = 1 << 1; // VOLUME_WRITE*, VOLUME_FLUSH
+pub const ACL_SNAPSHOT: u32 = 1 << 2; // CREATE_SNAPSHOT, REMOVE_SNAPSHOT
+pub const ACL_ADMIN: u32 = 1 << 3; // GET_ACL, SET_ACL, SET_INTEGRITY, SET_TUNING, policies, CREATE_VOLUME
+pub const ACL_ALL: u32 = ACL_READ | ACL_WRITE | ACL_SNAPSHOT | ACL_ADMIN;
+
+// What an ACL is attached to
+pub const ACL_TARGET_POOL: u32 = 0;
+pub const ACL_TARGET_VOLUME: u32 = 1;
+
+// Principal kinds of an entry on the wire
+pub const ACL_PRINCIPAL_EVERYONE: u32 = 0;
+pub const ACL_PRINCIPAL_CAPABILITY: u32 = 1;
+pub const ACL_PRINCIPAL_PROCESS: u32 = 2;
+
+/// Most entries one ACL holds
+pub const ACL_MAX_ENTRIES: usize = 64;
+
+/// Audit event types emitted by the LVM driver (user range, see capabilities.c)
+const AUDIT_STORAGE_DENIED: u32 = 0x1301;
+const AUDIT_STORAGE_ACL: u32 = 0x1302;
+const AUDIT_STORAGE_TUNING: u32 = 0x1303;
+const AUDIT_STORAGE_POLICY: u32 = 0x1304;
+
+/// Identity a control request arrived with, as the IPC layer reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Requester {
+    pub pid: u64,
+    /// Capability the request was made under
+    pub capability: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Principal {
+    Everyone,
+    /// Requests made under this capability, whichever process holds it
+    Capability(u64),
+    Process(u64),
+}
+
+impl Principal {
+    fn matches(&self, requester: &Requester) -> bool {
+        match *self {
+            Principal::Everyone => true,
+            Principal::Capability(capability) => capability == requester.capability,
+            Principal::Process(pid) => pid == requester.pid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AclEntry {
+    pub principal: Principal,
+    pub allow: u32,
+    pub deny: u32,
+}
+
+impl AclEntry {
+    fn read(reader: &mut ControlReader) -> Option<Self> {
+        let kind = reader.u32()?;
+        let id = reader.u64()?;
+        let principal = match kind {
+            ACL_PRINCIPAL_EVERYONE => Principal::Everyone,
+            ACL_PRINCIPAL_CAPABILITY => Principal::Capability(id),
+            ACL_PRINCIPAL_PROCESS => Principal::Process(id),
+            _ => return None,
+        };
+        let allow = reader.u32()?;
+        let deny = reader.u32()?;
+        if (allow | deny) & !ACL_ALL != 0 {
+            return None;
+        }
+        Some(Self { principal, allow, deny })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        let (kind, id) = match self.principal {
+            Principal::Everyone => (ACL_PRINCIPAL_EVERYONE, 0),
+            Principal::Capability(capability) => (ACL_PRINCIPAL_CAPABILITY, capability),
+            Principal::Process(pid) => (ACL_PRINCIPAL_PROCESS, pid),
+        };
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&id.to_le_bytes());
+        out.extend_from_slice(&self.allow.to_le_bytes());
+        out.extend_from_slice(&self.deny.to_le_bytes());
+    }
+}
+
+/// Entries of one pool or volume. The generation moves on with every
+/// update, so an administrator's read-modify-write only lands if nobody
+/// changed the list in between
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessControlList {
+    pub generation: u64,
+    pub entries: Vec<AclEntry>,
+}
+
+/// ACLs of pools and volumes.
+///
+/// A request is checked against the entries of its pool and of its
+/// volume together: the rights it gets are those allowed by any matching
+/// entry, minus those denied by any matching entry, so a deny on the pool
+/// cannot be undone by an allow on a volume. Pools and volumes without
+/// entries are open to everyone for everything but ACL_ADMIN, as they
+/// were before ACLs existed.
+pub struct AccessControl {
+    pools: BTreeMap<String, AccessControlList>,
+    volumes: BTreeMap<String, AccessControlList>,
+}
+
+impl AccessControl {
+    pub fn new() -> Self {
+        Self {
+            pools: BTreeMap::new(),
+            volumes: BTreeMap::new(),
+        }
+    }
+
+    fn lists(&self, target: u32) -> &BTreeMap<String, AccessControlList> {
+        if target == ACL_TARGET_POOL { &self.pools } else { &self.volumes }
+    }
+
+    pub fn get(&self, target: u32, name: &str) -> Option<&AccessControlList> {
+        self.lists(target).get(name)
+    }
+
+    /// Replace every entry of a pool or volume, provided its generation is
+    /// still `expected` (0 for one that never had an ACL). Returns the new
+    /// generation, or CTRL_ESTALE
+    pub fn replace(&mut self, target: u32, name: &str, expected: u64, entries: Vec<AclEntry>) -> Result<u64, i32> {
+        let lists = if target == ACL_TARGET_POOL { &mut self.pools } else { &mut self.volumes };
+        let acl = lists.entry(name.to_string()).or_default();
+        if acl.generation != expected {
+            return Err(CTRL_ESTALE);
+        }
+        acl.generation += 1;
+        acl.entries = entries;
+        Ok(acl.generation)
+    }
+
+    /// Drop the ACL of a pool or volume that no longer exists, so one
+    /// created later under the same name does not inherit it
+    pub fn remove(&mut self, target: u32, name: &str) {
+        let lists = if target == ACL_TARGET_POOL { &mut self.pools } else { &mut self.volumes };
+        lists.remove(name);
+    }
+
+    /// Rights of `requester` on a pool, or on a volume and its pool
+    pub fn rights(&self, requester: &Requester, pool: Option<&str>, volume: Option<&str>) -> u32 {
+        let entries: Vec<&AclEntry> = [pool.and_then(|name| self.pools.get(name)), volume.and_then(|name| self.volumes.get(name))]
+            .into_iter()
+            .flatten()
+            .flat_map(|acl| acl.entries.iter())
+            .collect();
+        if entries.is_empty() {
+            return ACL_ALL & !ACL_ADMIN;
+        }
+
+        let (mut allow, mut deny) = (0, 0);
+        for entry in entries.into_iter().filter(|entry| entry.principal.matches(requester)) {
+            allow |= entry.allow;
+            deny |= entry.deny;
+        }
+        allow & !deny
+    }
+}
+
+fn target_name(target: u32) -> &'static str {
+    if target == ACL_TARGET_POOL { "pool" } else { "volume" }
+}
+
 // ========================================
 // DRIVER ENTRY POINT
 // ========================================
@@ -1593,6 +3053,8 @@ mod tests {
                 offset: 0,
                 format_version: 2,
             },
+            device_class: DeviceClass::Hdd,
+            node: LOCAL_NODE.to_string(),
         };
         
         pv_mgr.add_physical_volume("/dev/sda".to_string(), pv);
@@ -1924,6 +3386,212 @@ mod tests {
         assert_eq!(result.unwrap_err(), DriverError::Unsupported);
     }
     
+    const ANYONE: Requester = Requester { pid: 100, capability: 0x10 };
+
+    fn control_request(opcode: u32, strings: &[&str], size: Option<u64>) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        for value in strings {
+            put_string(&mut request, value);
+        }
+        if let Some(size) = size {
+            request.extend_from_slice(&size.to_le_bytes());
+        }
+        request
+    }
+
+    fn control_status(reply: &[u8]) -> i32 {
+        i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]])
+    }
+
+    #[test]
+    fn test_control_protocol() {
+        let mut driver = LvmDriver::new();
+        driver.create_volume_group("vg0".to_string(), vec!["/dev/sda".to_string()]).unwrap();
+        driver.create_logical_volume("root".to_string(), 1 << 30, LvType::Linear, "vg0".to_string()).unwrap();
+
+        let reply = driver.handle_control(ANYONE, &control_request(CTRL_LIST_POOLS, &[], None));
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[4..8], &3u32.to_le_bytes());
+        assert_eq!(&reply[8..11], b"vg0");
+        assert_eq!(&reply[31..35], &1u32.to_le_bytes());
+
+        let create = control_request(CTRL_CREATE_SNAPSHOT, &["root-snap", "root"], Some(1 << 28));
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &create)), CTRL_OK);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &create)), CTRL_EEXIST);
+        let orphan = control_request(CTRL_CREATE_SNAPSHOT, &["other", "missing"], Some(1 << 28));
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &orphan)), CTRL_ENOENT);
+
+        let reply = driver.handle_control(ANYONE, &control_request(CTRL_LIST_SNAPSHOTS, &["vg0"], None));
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[8..17], b"root-snap");
+        assert_eq!(&reply[21..25], b"root");
+        let reply = driver.handle_control(ANYONE, &control_request(CTRL_LIST_SNAPSHOTS, &["vg1"], None));
+        assert_eq!(control_status(&reply), CTRL_ENOENT);
+
+        let remove = control_request(CTRL_REMOVE_SNAPSHOT, &["root-snap"], None);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &remove)), CTRL_OK);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &remove)), CTRL_ENOENT);
+
+        // Truncated requests are rejected
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &CTRL_CREATE_SNAPSHOT.to_le_bytes())), CTRL_EINVAL);
+
+        let reply = driver.handle_control(ANYONE, &control_request(CTRL_VOLUME_INFO, &["root"], None));
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[4..12], &(1u64 << 30).to_le_bytes());
+        assert_eq!(&reply[12..16], &4096u32.to_le_bytes());
+        let missing = control_request(CTRL_VOLUME_INFO, &["swap"], None);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &missing)), CTRL_ENOENT);
+
+        // Volume I/O stays on whole blocks inside the volume
+        for (offset, length) in [(512u64, 4096u32), (0, 100), ((1 << 30) - 4096, 8192), (0, 0)] {
+            let mut read = control_request(CTRL_VOLUME_READ, &["root"], Some(offset));
+            read.extend_from_slice(&length.to_le_bytes());
+            assert_eq!(control_status(&driver.handle_control(ANYONE, &read)), CTRL_EINVAL);
+        }
+        let mut write = control_request(CTRL_VOLUME_WRITE, &["swap"], Some(0));
+        write.extend_from_slice(&[0u8; 4096]);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &write)), CTRL_ENOENT);
+    }
+
+    fn acl_request(opcode: u32, target: u32, name: &str, generation: u64, entries: &[AclEntry]) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        request.extend_from_slice(&target.to_le_bytes());
+        put_string(&mut request, name);
+        if opcode == CTRL_SET_ACL {
+            request.extend_from_slice(&generation.to_le_bytes());
+            request.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            for entry in entries {
+                entry.write(&mut request);
+            }
+        }
+        request
+    }
+
+    #[test]
+    fn test_control_access_lists() {
+        let admin = Requester { pid: 1, capability: 0xa0 };
+        let backup = Requester { pid: 7, capability: 0xb0 };
+        let mut driver = LvmDriver::new();
+        driver.create_volume_group("vg0".to_string(), vec!["/dev/sda".to_string()]).unwrap();
+        driver.create_volume_group("vg1".to_string(), vec!["/dev/sdb".to_string()]).unwrap();
+        driver.create_logical_volume("root".to_string(), 1 << 30, LvType::Linear, "vg0".to_string()).unwrap();
+        driver.create_logical_volume("data".to_string(), 1 << 30, LvType::Linear, "vg0".to_string()).unwrap();
+
+        // Without ACLs everything but administration is open
+        let info = control_request(CTRL_VOLUME_INFO, &["root"], None);
+        assert_eq!(control_status(&driver.handle_control(backup, &info)), CTRL_OK);
+        let get = acl_request(CTRL_GET_ACL, ACL_TARGET_POOL, "vg0", 0, &[]);
+        assert_eq!(control_status(&driver.handle_control(admin, &get)), CTRL_EACCES);
+
+        // The pool is administered by one capability and readable by everyone
+        let pool_acl = [
+            AclEntry { principal: Principal::Capability(admin.capability), allow: ACL_ALL, deny: 0 },
+            AclEntry { principal: Principal::Everyone, allow: ACL_READ, deny: 0 },
+        ];
+        assert_eq!(driver.access.replace(ACL_TARGET_POOL, "vg0", 0, pool_acl.to_vec()), Ok(1));
+
+        let reply = driver.handle_control(admin, &get);
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[4..12], &1u64.to_le_bytes());
+        assert_eq!(&reply[12..16], &2u32.to_le_bytes());
+
+        // Backups may snapshot "root", but not write to it or touch "data"
+        let volume_acl = [AclEntry { principal: Principal::Process(backup.pid), allow: ACL_SNAPSHOT, deny: 0 }];
+        let set = acl_request(CTRL_SET_ACL, ACL_TARGET_VOLUME, "root", 0, &volume_acl);
+        let reply = driver.handle_control(admin, &set);
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[4..12], &1u64.to_le_bytes());
+        // A second update from the same generation lost the race
+        assert_eq!(control_status(&driver.handle_control(admin, &set)), CTRL_ESTALE);
+        assert_eq!(control_status(&driver.handle_control(backup, &set)), CTRL_EACCES);
+
+        let snapshot = control_request(CTRL_CREATE_SNAPSHOT, &["root-snap", "root"], Some(1 << 28));
+        assert_eq!(control_status(&driver.handle_control(backup, &snapshot)), CTRL_OK);
+        let other = control_request(CTRL_CREATE_SNAPSHOT, &["data-snap", "data"], Some(1 << 28));
+        assert_eq!(control_status(&driver.handle_control(backup, &other)), CTRL_EACCES);
+        let mut write = control_request(CTRL_VOLUME_WRITE, &["root"], Some(0));
+        write.extend_from_slice(&[0u8; 4096]);
+        assert_eq!(control_status(&driver.handle_control(backup, &write)), CTRL_EACCES);
+        // Snapshots fall under the ACL of their origin's pool
+        let remove = control_request(CTRL_REMOVE_SNAPSHOT, &["root-snap"], None);
+        assert_eq!(control_status(&driver.handle_control(backup, &remove)), CTRL_EACCES);
+        assert_eq!(control_status(&driver.handle_control(admin, &remove)), CTRL_OK);
+
+        // A pool deny wins over a volume allow
+        let pool_acl = [
+            AclEntry { principal: Principal::Capability(admin.capability), allow: ACL_ALL, deny: 0 },
+            AclEntry { principal: Principal::Everyone, allow: ACL_READ, deny: 0 },
+            AclEntry { principal: Principal::Process(backup.pid), allow: 0, deny: ACL_READ },
+        ];
+        let set = acl_request(CTRL_SET_ACL, ACL_TARGET_POOL, "vg0", 1, &pool_acl);
+        assert_eq!(control_status(&driver.handle_control(admin, &set)), CTRL_OK);
+        assert_eq!(control_status(&driver.handle_control(backup, &info)), CTRL_EACCES);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &info)), CTRL_OK);
+
+        // Pools the requester cannot read are left out of the listing
+        let reply = driver.handle_control(backup, &control_request(CTRL_LIST_POOLS, &[], None));
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[8..11], b"vg1");
+        assert_eq!(reply.len(), 4 + 4 + 3 + 8 + 8 + 4 + 4);
+
+        // Malformed lists change nothing
+        let mut bad = acl_request(CTRL_SET_ACL, ACL_TARGET_POOL, "vg0", 2, &pool_acl);
+        bad.truncate(bad.len() - 2);
+        assert_eq!(control_status(&driver.handle_control(admin, &bad)), CTRL_EINVAL);
+        let missing = acl_request(CTRL_GET_ACL, ACL_TARGET_VOLUME, "swap", 0, &[]);
+        assert_eq!(control_status(&driver.handle_control(admin, &missing)), CTRL_ENOENT);
+        assert_eq!(driver.access.get(ACL_TARGET_POOL, "vg0").unwrap().generation, 2);
+        assert_eq!(driver.stats.access_denied.load(Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    fn test_storage_policies() {
+        let mut driver = LvmDriver::new();
+        let devices = ["/dev/sda", "/dev/nvme0n1", "/dev/nvme1n1"];
+        for device in devices {
+            driver.create_physical_volume(device.to_string(), 1 << 30).unwrap();
+        }
+        driver.create_volume_group("vg0".to_string(), devices.iter().map(|device| device.to_string()).collect()).unwrap();
+        let fast = StoragePolicy::parse("policy fast\ncopies 2\nclasses nvme\nsnapshots every 1h keep 2").unwrap();
+        driver.storage.policies.insert("fast".to_string(), fast);
+
+        // Placed on the two NVMe devices when they have the room
+        assert_eq!(driver.create_policy_volume("vg0", "big", 2 << 30, "fast", 0), Err(CTRL_ENOSPC));
+        assert_eq!(driver.create_policy_volume("vg0", "db", 1 << 28, "slow", 0), Err(CTRL_ENOENT));
+        assert_eq!(driver.create_policy_volume("vg0", "db", 1 << 28, "fast", 0), Ok(()));
+        let lv = driver.lv_manager.get_logical_volume("db").unwrap();
+        assert_eq!(replicas_of(lv), ["/dev/nvme0n1", "/dev/nvme1n1"]);
+        assert_eq!(lv.lv_type, LvType::Mirrored);
+        assert_eq!(driver.pv_manager.get_physical_volume("/dev/nvme0n1").unwrap().free_pe_count, 256 - 64);
+        assert_eq!(driver.storage.snapshots["db"].len(), 1);
+
+        // A lost copy is kept until another NVMe device can replace it
+        driver.pv_manager.get_physical_volume_mut("/dev/nvme1n1").unwrap().state = PvState::Failed;
+        let start = driver.storage.snapshots["db"][0].created_ns;
+        driver.reconcile_volume("db", start + 1);
+        assert_eq!(driver.storage.violations["db"], [Violation::Degraded { healthy: 1, needed: 2 }]);
+        driver.pv_manager.get_physical_volume_mut("/dev/sda").unwrap().device_class = DeviceClass::Nvme;
+        driver.reconcile_volume("db", start + 2);
+        assert!(!driver.storage.violations.contains_key("db"));
+        assert_eq!(replicas_of(driver.lv_manager.get_logical_volume("db").unwrap()), ["/dev/nvme0n1", "/dev/sda"]);
+
+        // Hourly snapshots, the latest two kept
+        let hour = 3600 * 1_000_000_000;
+        driver.reconcile_volume("db", start + hour);
+        driver.reconcile_volume("db", start + 2 * hour);
+        let taken: Vec<&str> = driver.storage.snapshots["db"].iter().map(|snapshot| snapshot.name.as_str()).collect();
+        assert_eq!(taken.len(), 2);
+        assert!(driver.snapshot_manager.get_snapshot(taken[0]).is_some());
+        assert_eq!(driver.snapshot_manager.get_all_snapshots().len(), 2);
+
+        let reply = driver.handle_control(ANYONE, &control_request(CTRL_POLICY_STATUS, &["db"], None));
+        assert_eq!(control_status(&reply), CTRL_OK);
+        assert_eq!(&reply[8..12], b"fast");
+        assert_eq!(&reply[12..24], &[2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let remove = control_request(CTRL_REMOVE_POLICY, &["fast"], None);
+        assert_eq!(control_status(&driver.handle_control(ANYONE, &remove)), CTRL_EACCES);
+    }
+
     #[test]
     fn test_uuid_generation() {
         let uuid1 = generate_uuid();
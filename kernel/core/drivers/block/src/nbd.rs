@@ -14,17 +14,18 @@ use alloc::{
     sync::Arc,
 };
 use core::{
-    sync::atomic::{AtomicU64, Ordering},
+    sync::atomic::AtomicU64,
     time::Duration,
     mem,
     ptr,
     slice,
 };
 use orion_async::{
-    Future, Pin, Poll, Context, Waker,
+    Future, Pin, Poll, Context, Waker, Elapsed, timeout,
     sync::{AsyncMutex, AsyncRwLock},
     channel::AsyncChannel,
 };
+use orion_sys::{clock_get, nanosleep};
 use orion_driver::{
     OrionDriver, BlockDriver, DeviceInfo, DriverError, DriverResult,
     MessageLoop, ReceivedMessage, IpcInterface,
@@ -34,6 +35,12 @@ use orion_block::{
     BlockRequest, BlockResponse, BlockDevice, BlockStats,
     CacheManager, SmartData, AsyncDriver,
 };
+use orion_blockstats::{BlockStatistics, BlockStatsSource, Operation};
+use orion_blkio::{Recovery, TimeoutPolicy, Watchdog, REQ_FUA, REQ_PREFLUSH};
+use orion_backup::{
+    BackupError, BackupJob, BackupSink, BackupSource, BackupSummary, ChangeTracker, Restore, RestoreSummary,
+    stream::DEFAULT_EXTENT_SIZE,
+};
 
 /// NBD Driver - Ultra-Modern Network Block Device Support for Remote Storage
 ///
@@ -77,45 +84,56 @@ pub struct NbdDriver {
     multipath_manager: MultiPathManager,
     /// Migration manager
     migration_manager: MigrationManager,
+    /// Changed-block tracking, once backups are enabled
+    change_tracker: Option<ChangeTracker>,
+    /// Deadlines of network requests and the reconnect escalation
+    watchdog: Watchdog,
+    /// Id of the next network request
+    next_request: u64,
     /// Message loop
     message_loop: MessageLoop,
     /// IPC interface
     ipc: IpcInterface,
 }
 
+orion_stats::counter_set! {
+    /// NBD operation counters, kept per CPU so concurrent requests never
+    /// share a cache line for them
+    pub struct NbdCounters => NbdCounterValues {
+        /// Total bytes read
+        bytes_read,
+        /// Total bytes written
+        bytes_written,
+        /// Total operations
+        total_operations,
+        /// Read operations
+        read_operations,
+        /// Write operations
+        write_operations,
+        /// Trim operations
+        trim_operations,
+        /// Flush operations
+        flush_operations,
+        /// Error count
+        error_count,
+        /// Network errors
+        network_errors,
+        /// Reconnection attempts
+        reconnection_attempts,
+        /// Cache hits
+        cache_hits,
+        /// Cache misses
+        cache_misses,
+    }
+}
+
 /// NBD Statistics
 #[derive(Debug, Default)]
 pub struct NbdStats {
-    /// Total bytes read
-    pub bytes_read: AtomicU64,
-    /// Total bytes written
-    pub bytes_written: AtomicU64,
-    /// Total operations
-    pub total_operations: AtomicU64,
-    /// Read operations
-    pub read_operations: AtomicU64,
-    /// Write operations
-    pub write_operations: AtomicU64,
-    /// Trim operations
-    pub trim_operations: AtomicU64,
-    /// Flush operations
-    pub flush_operations: AtomicU64,
-    /// Error count
-    pub error_count: AtomicU64,
-    /// Average latency (nanoseconds)
-    pub avg_latency: AtomicU64,
-    /// Max latency (nanoseconds)
-    pub max_latency: AtomicU64,
-    /// Min latency (nanoseconds)
-    pub min_latency: AtomicU64,
-    /// Network errors
-    pub network_errors: AtomicU64,
-    /// Reconnection attempts
-    pub reconnection_attempts: AtomicU64,
-    /// Cache hits
-    pub cache_hits: AtomicU64,
-    /// Cache misses
-    pub cache_misses: AtomicU64,
+    /// Operation and error counters
+    pub counters: NbdCounters,
+    /// Latency histograms and throughput per operation type
+    pub io: BlockStatistics,
     /// Compression ratio
     pub compression_ratio: AtomicU64,
     /// Encryption overhead
@@ -392,16 +410,19 @@ pub enum AuthMethod {
 }
 
 /// Credentials
+///
+/// Secrets never live in the driver: they are stored by the keyring server
+/// and referenced here by handle.
 #[derive(Debug, Clone)]
 pub struct Credentials {
     /// Username
     pub username: String,
-    /// Password hash
-    pub password_hash: String,
-    /// Certificate data
-    pub certificate_data: Option<Vec<u8>>,
-    /// Key data
-    pub key_data: Option<Vec<u8>>,
+    /// Keyring handle of the password hash
+    pub password_handle: Option<u64>,
+    /// Keyring handle of the client certificate
+    pub certificate_handle: Option<u64>,
+    /// Keyring handle of the private key
+    pub key_handle: Option<u64>,
     /// Expiration
     pub expiration: Option<u64>,
 }
@@ -1041,6 +1062,9 @@ impl NbdDriver {
             encryption_manager: EncryptionManager::new(),
             multipath_manager: MultiPathManager::new(),
             migration_manager: MigrationManager::new(),
+            change_tracker: None,
+            watchdog: Watchdog::new(TimeoutPolicy::DEFAULT),
+            next_request: 0,
             message_loop: MessageLoop::new(),
             ipc: IpcInterface::new(),
         }
@@ -1265,7 +1289,14 @@ impl NbdDriver {
         };
 
         let start_time = self.get_timestamp();
-        
+        let (operation, bytes) = match request_header.command {
+            NBD_CMD_READ => (Operation::Read, request_header.length as u64),
+            NBD_CMD_WRITE | NBD_CMD_WRITE_ZEROES => (Operation::Write, request_header.length as u64),
+            NBD_CMD_FLUSH => (Operation::Flush, 0),
+            NBD_CMD_TRIM => (Operation::Trim, 0),
+            _ => (Operation::Other, 0),
+        };
+
         let result = match request_header.command {
             NBD_CMD_READ => self.handle_read_command(&request_header, &data[mem::size_of::<NbdRequestHeader>()..]).await,
             NBD_CMD_WRITE => self.handle_write_command(&request_header, &data[mem::size_of::<NbdRequestHeader>()..]).await,
@@ -1280,8 +1311,11 @@ impl NbdDriver {
         };
 
         // Update latency statistics
-        let latency = self.get_timestamp() - start_time;
-        self.update_latency_stats(latency);
+        let latency = self.get_timestamp().saturating_sub(start_time);
+        match result {
+            Ok(_) => self.stats.io.record(operation, bytes, latency),
+            Err(_) => self.stats.io.record_error(operation),
+        }
 
         result
     }
@@ -1297,9 +1331,9 @@ impl NbdDriver {
         let read_data = self.read_data(header.offset, header.length as u64).await?;
         
         // Update statistics
-        self.stats.read_operations.fetch_add(1, Ordering::Relaxed);
-        self.stats.bytes_read.fetch_add(header.length as u64, Ordering::Relaxed);
-        self.stats.total_operations.fetch_add(1, Ordering::Relaxed);
+        self.stats.counters.read_operations().inc();
+        self.stats.counters.bytes_read().add(header.length as u64);
+        self.stats.counters.total_operations().inc();
 
         // Build response
         let mut response = Vec::new();
@@ -1319,12 +1353,13 @@ impl NbdDriver {
         }
 
         // Write data to cache or network
-        self.write_data(header.offset, data).await?;
+        let fua = { header.flags } & NBD_CMD_FLAG_FUA != 0;
+        self.write_data(header.offset, data, fua).await?;
         
         // Update statistics
-        self.stats.write_operations.fetch_add(1, Ordering::Relaxed);
-        self.stats.bytes_written.fetch_add(header.length as u64, Ordering::Relaxed);
-        self.stats.total_operations.fetch_add(1, Ordering::Relaxed);
+        self.stats.counters.write_operations().inc();
+        self.stats.counters.bytes_written().add(header.length as u64);
+        self.stats.counters.total_operations().inc();
 
         // Build response
         let mut response = Vec::new();
@@ -1355,8 +1390,8 @@ impl NbdDriver {
         self.flush_data().await?;
         
         // Update statistics
-        self.stats.flush_operations.fetch_add(1, Ordering::Relaxed);
-        self.stats.total_operations.fetch_add(1, Ordering::Relaxed);
+        self.stats.counters.flush_operations().inc();
+        self.stats.counters.total_operations().inc();
 
         // Build response
         let mut response = Vec::new();
@@ -1373,8 +1408,8 @@ impl NbdDriver {
         self.trim_data(header.offset, header.length as u64).await?;
         
         // Update statistics
-        self.stats.trim_operations.fetch_add(1, Ordering::Relaxed);
-        self.stats.total_operations.fetch_add(1, Ordering::Relaxed);
+        self.stats.counters.trim_operations().inc();
+        self.stats.counters.total_operations().inc();
 
         // Build response
         let mut response = Vec::new();
@@ -1402,12 +1437,13 @@ impl NbdDriver {
     /// Handle write zeroes command
     async fn handle_write_zeroes_command(&mut self, header: &NbdRequestHeader) -> DriverResult<Vec<u8>> {
         // Write zeroes
-        self.write_zeroes(header.offset, header.length as u64).await?;
+        let fua = { header.flags } & NBD_CMD_FLAG_FUA != 0;
+        self.write_zeroes(header.offset, header.length as u64, fua).await?;
         
         // Update statistics
-        self.stats.write_operations.fetch_add(1, Ordering::Relaxed);
-        self.stats.bytes_written.fetch_add(header.length as u64, Ordering::Relaxed);
-        self.stats.total_operations.fetch_add(1, Ordering::Relaxed);
+        self.stats.counters.write_operations().inc();
+        self.stats.counters.bytes_written().add(header.length as u64);
+        self.stats.counters.total_operations().inc();
 
         // Build response
         let mut response = Vec::new();
@@ -1478,11 +1514,11 @@ impl NbdDriver {
     async fn read_data(&mut self, offset: u64, length: u64) -> DriverResult<Vec<u8>> {
         // Try cache first
         if let Some(cached_data) = self.cache_manager.get(offset, length).await? {
-            self.stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+            self.stats.counters.cache_hits().inc();
             return Ok(cached_data);
         }
 
-        self.stats.cache_misses.fetch_add(1, Ordering::Relaxed);
+        self.stats.counters.cache_misses().inc();
         
         // Read from network
         let data = self.read_from_network(offset, length).await?;
@@ -1493,17 +1529,31 @@ impl NbdDriver {
         Ok(data)
     }
 
-    /// Write data
-    async fn write_data(&mut self, offset: u64, data: &[u8]) -> DriverResult<()> {
+    /// Write data, durable before this returns if `fua`
+    async fn write_data(&mut self, offset: u64, data: &[u8], fua: bool) -> DriverResult<()> {
+        // Track the blocks before they change so no backup can miss them
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.record_write(offset, data.len() as u64);
+        }
+
         // Write to cache
         self.cache_manager.put(offset, data).await?;
         
         // Write to network
-        self.write_to_network(offset, data).await?;
+        self.write_to_network(offset, data, fua).await?;
         
         Ok(())
     }
 
+    /// Write honouring REQ_PREFLUSH and REQ_FUA (see orion_blkio::durability).
+    /// Requests go out one at a time, so nothing passes the preflush.
+    pub async fn write_ordered(&mut self, offset: u64, data: &[u8], flags: u8) -> DriverResult<()> {
+        if flags & REQ_PREFLUSH != 0 {
+            self.flush_data().await?;
+        }
+        self.write_data(offset, data, flags & REQ_FUA != 0).await
+    }
+
     /// Disconnect connection
     async fn disconnect_connection(&mut self, handle: u64) -> DriverResult<()> {
         // Close connection
@@ -1524,6 +1574,10 @@ impl NbdDriver {
 
     /// Trim data
     async fn trim_data(&mut self, offset: u64, length: u64) -> DriverResult<()> {
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.record_write(offset, length);
+        }
+
         // Trim cache
         self.cache_manager.trim(offset, length).await?;
         
@@ -1542,12 +1596,12 @@ impl NbdDriver {
     }
 
     /// Write zeroes
-    async fn write_zeroes(&mut self, offset: u64, length: u64) -> DriverResult<()> {
+    async fn write_zeroes(&mut self, offset: u64, length: u64, fua: bool) -> DriverResult<()> {
         // Create zero data
         let zero_data = vec![0u8; length as usize];
         
         // Write to cache and network
-        self.write_data(offset, &zero_data).await?;
+        self.write_data(offset, &zero_data, fua).await?;
         
         Ok(())
     }
@@ -1562,6 +1616,9 @@ impl NbdDriver {
     async fn resize_device(&mut self, new_size: u64) -> DriverResult<()> {
         // Resize device
         self.resize_network_device(new_size).await?;
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.resize(new_size);
+        }
         Ok(())
     }
 
@@ -1578,29 +1635,106 @@ impl NbdDriver {
     /// Read from network
     async fn read_from_network(&mut self, offset: u64, length: u64) -> DriverResult<Vec<u8>> {
         // Read from active connection
+        let id = self.begin_request()?;
         let connection = self.connection_manager.get_active_connection().await?;
-        connection.read_data(offset, length).await
+        self.watchdog.start(id, monotonic_ns());
+        let outcome = timeout(self.watchdog.policy().command_ns, connection.read_data(offset, length)).await;
+        self.settle_request(id, outcome).await
     }
 
-    /// Write to network
-    async fn write_to_network(&mut self, offset: u64, data: &[u8]) -> DriverResult<()> {
+    /// Write to network. FUA is the command flag on exports that accept it
+    /// and a flush behind the write on the others.
+    async fn write_to_network(&mut self, offset: u64, data: &[u8], fua: bool) -> DriverResult<()> {
         // Write to active connection
+        let id = self.begin_request()?;
         let connection = self.connection_manager.get_active_connection().await?;
-        connection.write_data(offset, data).await
+        let native_fua = fua && connection.export_info.fua_support;
+        let flags = if native_fua { NBD_CMD_FLAG_FUA } else { 0 };
+        self.watchdog.start(id, monotonic_ns());
+        let outcome = timeout(self.watchdog.policy().command_ns, connection.write_data(offset, data, flags)).await;
+        self.settle_request(id, outcome).await?;
+
+        if fua && !native_fua {
+            self.flush_network().await?;
+        }
+        Ok(())
     }
 
     /// Flush network
     async fn flush_network(&mut self) -> DriverResult<()> {
         // Flush active connection
+        let id = self.begin_request()?;
         let connection = self.connection_manager.get_active_connection().await?;
-        connection.flush().await
+        self.watchdog.start(id, monotonic_ns());
+        let outcome = timeout(self.watchdog.policy().command_ns, connection.flush()).await;
+        self.settle_request(id, outcome).await
     }
 
     /// Trim network
     async fn trim_network(&mut self, offset: u64, length: u64) -> DriverResult<()> {
         // Trim active connection
+        let id = self.begin_request()?;
         let connection = self.connection_manager.get_active_connection().await?;
-        connection.trim(offset, length).await
+        self.watchdog.start(id, monotonic_ns());
+        let outcome = timeout(self.watchdog.policy().command_ns, connection.trim(offset, length)).await;
+        self.settle_request(id, outcome).await
+    }
+
+    /// Id for the next network request, refused once the export was given up
+    fn begin_request(&mut self) -> DriverResult<u64> {
+        if self.watchdog.is_offline() {
+            return Err(DriverError::DeviceNotReady);
+        }
+        self.next_request += 1;
+        Ok(self.next_request)
+    }
+
+    /// Finish network request `id`, run under the command deadline. A
+    /// request past it is aborted by reconnecting, as its reply cannot
+    /// arrive once the socket is gone (see orion_blkio::timeout).
+    async fn settle_request<T>(&mut self, id: u64, outcome: Result<DriverResult<T>, Elapsed>) -> DriverResult<T> {
+        match outcome {
+            Ok(result) => {
+                if self.watchdog.finish(id) {
+                    return Err(DriverError::Timeout);
+                }
+                result
+            }
+            Err(Elapsed) => {
+                self.watchdog.expire(id);
+                self.recover().await;
+                if self.watchdog.is_offline() {
+                    return Err(DriverError::DeviceNotReady);
+                }
+                Err(DriverError::Timeout)
+            }
+        }
+    }
+
+    /// Reconnect the active connection, then every connection, and give
+    /// the export up when that does not help
+    async fn recover(&mut self) {
+        while let Some(step) = self.watchdog.poll(monotonic_ns()) {
+            match step {
+                Recovery::Abort(id) => match self.connection_manager.reconnect_active().await {
+                    // Nothing more comes for the request on the new socket
+                    Ok(()) => {
+                        self.watchdog.finish(id);
+                    }
+                    Err(_) => self.watchdog.abort_failed(id),
+                },
+                Recovery::Reset => {
+                    let reconnected = self.connection_manager.reconnect_all().await;
+                    self.watchdog.reset_done(reconnected.is_ok());
+                }
+                Recovery::Offline => {
+                    self.watchdog.abandon();
+                }
+            }
+        }
+        if self.watchdog.is_offline() {
+            self.state = DeviceState::Error;
+        }
     }
 
     /// Get block status from network
@@ -1626,30 +1760,6 @@ impl NbdDriver {
             .as_nanos() as u64
     }
 
-    /// Update latency statistics
-    fn update_latency_stats(&self, latency: u64) {
-        let current_avg = self.stats.avg_latency.load(Ordering::Relaxed);
-        let current_max = self.stats.max_latency.load(Ordering::Relaxed);
-        let current_min = self.stats.min_latency.load(Ordering::Relaxed);
-        
-        // Update average (simple moving average)
-        let new_avg = if current_avg == 0 {
-            latency
-        } else {
-            (current_avg + latency) / 2
-        };
-        
-        // Update max
-        let new_max = if latency > current_max { latency } else { current_max };
-        
-        // Update min
-        let new_min = if current_min == 0 || latency < current_min { latency } else { current_min };
-        
-        self.stats.avg_latency.store(new_avg, Ordering::Relaxed);
-        self.stats.max_latency.store(new_max, Ordering::Relaxed);
-        self.stats.min_latency.store(new_min, Ordering::Relaxed);
-    }
-
     /// Discover NBD servers
     pub async fn discover_servers(&mut self) -> DriverResult<Vec<NbdServer>> {
         self.server_manager.discover_servers().await
@@ -1673,7 +1783,7 @@ impl NbdDriver {
                 Ok(BlockResponse::Read { data })
             }
             BlockRequest::Write { offset, data } => {
-                self.write_data(offset, &data).await?;
+                self.write_data(offset, &data, false).await?;
                 Ok(BlockResponse::Write { bytes_written: data.len() as u64 })
             }
             BlockRequest::Trim { offset, length } => {
@@ -1696,6 +1806,115 @@ impl NbdDriver {
     }
 }
 
+// ========================================
+// CHANGED-BLOCK TRACKING AND BACKUP
+// ========================================
+
+/// Granularity of change tracking: 16 KiB of bitmap per TiB of export
+const CBT_BLOCK_SIZE: u32 = 64 * 1024;
+
+fn backup_error(error: BackupError) -> DriverError {
+    match error {
+        BackupError::InvalidArgument => DriverError::InvalidParameter,
+        BackupError::Busy => DriverError::ResourceBusy,
+        BackupError::Io => DriverError::IoError,
+        _ => DriverError::InvalidData,
+    }
+}
+
+impl NbdDriver {
+    /// Start tracking the blocks written to the export (requires
+    /// `backup_support`). `saved` is the tracking record stored with the
+    /// volume metadata by the previous run. The record returned must be
+    /// stored in its place before the export takes writes; it is marked
+    /// unclean until the one from `change_tracking_record(true)` replaces
+    /// it at orderly shutdown, so a crash forces the next backup to be full.
+    pub fn enable_change_tracking(
+        &mut self,
+        config: &NbdConfig,
+        volume_size: u64,
+        saved: Option<&[u8]>,
+    ) -> DriverResult<Vec<u8>> {
+        if !config.backup_support {
+            return Err(DriverError::Unsupported);
+        }
+        let block_size = CBT_BLOCK_SIZE.max(config.block_size.next_power_of_two());
+        let tracker = ChangeTracker::load(saved, volume_size, block_size).map_err(backup_error)?;
+        let record = tracker.encode(false);
+        self.change_tracker = Some(tracker);
+        Ok(record)
+    }
+
+    /// Current tracking record, to store with the volume metadata after a
+    /// backup completes and, clean, at shutdown
+    pub fn change_tracking_record(&self, clean: bool) -> Option<Vec<u8>> {
+        self.change_tracker.as_ref().map(|tracker| tracker.encode(clean))
+    }
+
+    /// Stream the blocks changed since the last backup, or the whole
+    /// export when `full` is set or no backup was taken yet, to `sink`
+    /// (a file, another NBD export or a remote node). Blocks written
+    /// while the backup runs are sent again by the next one; a failed
+    /// backup leaves all of its blocks to the next one.
+    pub async fn backup(&mut self, full: bool, sink: &mut dyn BackupSink) -> DriverResult<BackupSummary> {
+        let tracker = self.change_tracker.as_mut().ok_or(DriverError::Unsupported)?;
+        let point = tracker.begin_backup(full).map_err(backup_error)?;
+        let mut job = BackupJob::new(point, DEFAULT_EXTENT_SIZE);
+
+        let result = self.stream_backup(&mut job, sink).await;
+        let tracker = self.change_tracker.as_mut().ok_or(DriverError::InvalidState)?;
+        match result {
+            Ok(summary) => {
+                tracker.complete_backup(summary.generation).map_err(backup_error)?;
+                Ok(summary)
+            }
+            Err(error) => {
+                tracker.abort_backup(job.into_point());
+                Err(error)
+            }
+        }
+    }
+
+    async fn stream_backup(&mut self, job: &mut BackupJob, sink: &mut dyn BackupSink) -> DriverResult<BackupSummary> {
+        sink.write(&job.header()).map_err(backup_error)?;
+        while let Some(extent) = job.next_extent() {
+            let data = self.read_data(extent.offset, extent.length as u64).await?;
+            let record = job.encode_extent(extent, &data).map_err(backup_error)?;
+            sink.write(&record).map_err(backup_error)?;
+        }
+        let (trailer, summary) = job.finish();
+        sink.write(&trailer).map_err(backup_error)?;
+        Ok(summary)
+    }
+
+    /// Write a backup stream back onto the export. `restored` is the
+    /// generation the export holds, None unless an incremental stream is
+    /// applied on top of the full one it follows. Extents are written only
+    /// after their digest checked out.
+    pub async fn restore(&mut self, source: &mut dyn BackupSource, restored: Option<u64>) -> DriverResult<RestoreSummary> {
+        let mut restore = Restore::new(restored);
+        let mut chunk = vec![0u8; 64 * 1024];
+        loop {
+            let read = source.read(&mut chunk).map_err(backup_error)?;
+            if read == 0 {
+                return restore.finish().map_err(backup_error);
+            }
+
+            // Parsing is synchronous: collect the verified extents, then write them
+            let mut extents: Vec<(u64, Vec<u8>)> = Vec::new();
+            restore
+                .feed(&chunk[..read], &mut |offset: u64, data: &[u8]| {
+                    extents.push((offset, data.to_vec()));
+                    Ok(())
+                })
+                .map_err(backup_error)?;
+            for (offset, data) in extents {
+                self.write_data(offset, &data, false).await?;
+            }
+        }
+    }
+}
+
 // Implement OrionDriver trait
 impl OrionDriver for NbdDriver {
     async fn initialize(&mut self) -> DriverResult<()> {
@@ -1785,7 +2004,7 @@ impl BlockDriver for NbdDriver {
     }
 
     async fn write(&mut self, offset: u64, data: &[u8]) -> DriverResult<u64> {
-        self.write_data(offset, data).await?;
+        self.write_data(offset, data, false).await?;
         Ok(data.len() as u64)
     }
 
@@ -1812,18 +2031,20 @@ impl BlockDriver for NbdDriver {
     }
 
     async fn get_block_stats(&mut self) -> DriverResult<BlockStats> {
+        let latency = self.stats.io.report().latency;
+        let counters = self.stats.counters.snapshot();
         Ok(BlockStats {
-            bytes_read: self.stats.bytes_read.load(Ordering::Relaxed),
-            bytes_written: self.stats.bytes_written.load(Ordering::Relaxed),
-            total_operations: self.stats.total_operations.load(Ordering::Relaxed),
-            read_operations: self.stats.read_operations.load(Ordering::Relaxed),
-            write_operations: self.stats.write_operations.load(Ordering::Relaxed),
-            trim_operations: self.stats.trim_operations.load(Ordering::Relaxed),
-            flush_operations: self.stats.flush_operations.load(Ordering::Relaxed),
-            error_count: self.stats.error_count.load(Ordering::Relaxed),
-            avg_latency: self.stats.avg_latency.load(Ordering::Relaxed),
-            max_latency: self.stats.max_latency.load(Ordering::Relaxed),
-            min_latency: self.stats.min_latency.load(Ordering::Relaxed),
+            bytes_read: counters.bytes_read,
+            bytes_written: counters.bytes_written,
+            total_operations: counters.total_operations,
+            read_operations: counters.read_operations,
+            write_operations: counters.write_operations,
+            trim_operations: counters.trim_operations,
+            flush_operations: counters.flush_operations,
+            error_count: counters.error_count,
+            avg_latency: latency.mean,
+            max_latency: latency.max,
+            min_latency: latency.min,
         })
     }
 
@@ -1844,6 +2065,12 @@ impl BlockDriver for NbdDriver {
     }
 }
 
+impl BlockStatsSource for NbdDriver {
+    fn block_statistics(&self) -> &BlockStatistics {
+        &self.stats.io
+    }
+}
+
 impl NbdDriver {
     /// Handle device added event
     async fn handle_device_added(&mut self, device_id: String) -> DriverResult<()> {
@@ -1877,7 +2104,7 @@ impl NbdDriver {
                 Ok(orion_driver::IoResponse::Read { data })
             }
             orion_driver::IoMessage::Write { offset, data } => {
-                self.write_data(offset, &data).await?;
+                self.write_data(offset, &data, false).await?;
                 Ok(orion_driver::IoResponse::Write { bytes_written: data.len() as u64 })
             }
             orion_driver::IoMessage::Trim { offset, length } => {
@@ -2016,6 +2243,22 @@ impl ConnectionManager {
         }
     }
 
+    /// Drop the active connection and connect it again
+    pub async fn reconnect_active(&mut self) -> DriverResult<()> {
+        let connection = self.get_active_connection().await?;
+        connection.close().await?;
+        connection.connect().await
+    }
+
+    /// Drop every connection and connect them again
+    pub async fn reconnect_all(&mut self) -> DriverResult<()> {
+        for connection in self.connections.values_mut() {
+            connection.close().await?;
+            connection.connect().await?;
+        }
+        Ok(())
+    }
+
     fn generate_connection_id(&self) -> u32 {
         // Simple ID generation
         self.connections.len() as u32 + 1
@@ -2063,7 +2306,8 @@ impl NbdConnection {
         Ok(data)
     }
 
-    pub async fn write_data(&mut self, offset: u64, data: &[u8]) -> DriverResult<()> {
+    /// Send WRITE with the command `flags` (NBD_CMD_FLAG_FUA)
+    pub async fn write_data(&mut self, offset: u64, data: &[u8], flags: u16) -> DriverResult<()> {
         // Write data to network
         Ok(())
     }
@@ -2470,17 +2714,26 @@ impl Default for NbdServer {
     }
 }
 
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn idle_ns(duration: u64) {
+    let _ = nanosleep(duration);
+}
+
 // Main driver entry point
 #[no_mangle]
 pub extern "C" fn driver_main() -> i32 {
     // Create and initialize NBD driver
     let mut driver = NbdDriver::new();
     
-    // Run async initialization
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    let result = runtime.block_on(async {
-        driver.initialize().await
-    });
+    // Run async initialization, timers driven by the monotonic clock
+    orion_async::set_clock(monotonic_ns);
+    orion_async::set_idle(idle_ns);
+    let result = orion_async::block_on(driver.initialize());
     
     match result {
         Ok(_) => {
@@ -2726,6 +2979,16 @@ mod tests {
         let response = driver.process_command(&command_data).await.unwrap();
         assert!(!response.is_empty());
         assert_eq!(response[0..4], NBD_CLISERV_MAGIC.to_le_bytes());
+
+        // Every command landed in the histogram of its operation type
+        let report = driver.block_stats_report();
+        assert_eq!((report.read.operations, report.read.bytes), (1, 512));
+        assert_eq!((report.write.operations, report.write.bytes), (1, 512));
+        assert_eq!((report.flush.operations, report.trim.operations, report.other.operations), (1, 1, 1));
+        assert_eq!(report.latency.count, 5);
+
+        driver.reset_block_stats();
+        assert_eq!(driver.block_stats_report().total_operations(), 0);
     }
 
     #[tokio::test]
@@ -2902,7 +3165,7 @@ mod tests {
         assert_eq!(data.len(), 512);
         
         let test_data = vec![0xDDu8; 512];
-        let result = connection.write_data(0, &test_data).await;
+        let result = connection.write_data(0, &test_data, NBD_CMD_FLAG_FUA).await;
         assert!(result.is_ok());
         
         let result = connection.flush().await;
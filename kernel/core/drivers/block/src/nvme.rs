@@ -41,6 +41,21 @@
      AsyncDriver, MessageLoop, ReceivedMessage, IpcInterface,
      PowerState, DeviceState, HotplugEvent, CacheManager, SmartData,
  };
+use orion_blockstats::{BlockStatistics, BlockStatsSource, Operation};
+use orion_blkio::{
+    encode_nvme_dsm, BarrierQueue, Command, DeviceCache, DiscardBatcher, DiscardLimits, DiscardRange, IoOp, IoStatus,
+    Recovery, Request, TimeoutPolicy, Watchdog, BLK_IOCTL_CONTROL, REQ_PREFLUSH,
+};
+use orion_blkio::control::{
+    encode_info, encode_integrity_read, ControlRequest, DiskInfo, STATUS_EBADMSG, STATUS_EINVAL, STATUS_OK,
+};
+use orion_fwupdate::{
+    handle_control, FirmwareDevice, FirmwareInfo, FirmwareUpdater, FwError, Transport, FW_IOCTL_CONTROL,
+};
+use orion_ipc::IpcChannel;
+use orion_proto::reply;
+use orion_sys::clock_get;
+use orion_thermal::{kelvin_to_mc, ThermalSensor};
 
  // ========================================
  // ADDITIONAL STRUCTURES FOR MANAGERS
@@ -48,6 +63,100 @@
 
 use core::time::Duration;
 
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+// NVM command set opcodes used for discard and write ordering
+const NVME_CMD_FLUSH: u8 = 0x00;
+const NVME_CMD_DSM: u8 = 0x09;
+
+// Force Unit Access bit of a write (CDW12)
+const NVME_RW_FUA: u32 = 1 << 30;
+// Deallocate attribute of Dataset Management (CDW11)
+const NVME_DSM_DEALLOCATE: u32 = 1 << 2;
+
+// Identify Controller fields beyond the decoded structure
+const NVME_ID_ONCS_OFFSET: usize = 520;
+const NVME_ID_VWC_OFFSET: usize = 525;
+const NVME_ONCS_DSM: u16 = 1 << 2;
+const NVME_VWC_PRESENT: u8 = 1 << 0;
+
+// Abort of a timed out I/O command
+const NVME_ADMIN_ABORT: u8 = 0x08;
+const NVME_ABORT_COMMAND_ID: u16 = 109;
+
+// Completion status: phase bit, status code and status code type
+const NVME_STATUS_SC_SHIFT: u16 = 1;
+const NVME_STATUS_SC_MASK: u16 = 0xFF;
+const NVME_STATUS_SCT_SHIFT: u16 = 9;
+const NVME_STATUS_SCT_MASK: u16 = 0x7;
+const NVME_SCT_GENERIC: u16 = 0;
+const NVME_SCT_MEDIA: u16 = 2;
+const NVME_SC_ABORT_REQUESTED: u16 = 0x07;
+
+// Admin opcodes used for firmware updates
+const NVME_ADMIN_GET_LOG_PAGE: u8 = 0x02;
+const NVME_ADMIN_FW_COMMIT: u8 = 0x10;
+const NVME_ADMIN_FW_DOWNLOAD: u8 = 0x11;
+const NVME_LOG_FW_SLOT: u32 = 0x03;
+const NVME_LOG_FW_SLOT_SIZE: usize = 512;
+
+// Firmware Commit actions (CDW10 bits 5:3)
+const NVME_FW_CA_REPLACE: u32 = 0b000;
+const NVME_FW_CA_ACTIVATE_AT_RESET: u32 = 0b010;
+const NVME_FW_CA_ACTIVATE_NOW: u32 = 0b011;
+
+// Firmware Commit status codes (command specific) asking for a reset
+const NVME_SC_FW_NEEDS_CONVENTIONAL_RESET: u32 = 0x0B;
+const NVME_SC_FW_NEEDS_SUBSYSTEM_RESET: u32 = 0x10;
+const NVME_SC_FW_NEEDS_RESET: u32 = 0x11;
+const NVME_SCT_COMMAND_SPECIFIC: u32 = 1;
+
+const NVME_OACS_FW: u32 = 1 << 2;
+const NVME_FRMW_SLOT1_RO: u8 = 1 << 0;
+const NVME_NSSR_OFFSET: u64 = 0x20;
+const NVME_NSSR_RESET: u32 = 0x4E56_4D65; // "NVMe"
+
+/// Largest firmware payload accepted for download
+const NVME_FW_MAX_IMAGE: u32 = 64 * 1024 * 1024;
+/// Unit of the firmware update granularity (FWUG) field
+const NVME_FW_GRANULARITY_UNIT: usize = 4096;
+
+// SMART / Health Information log, read for the thermal server
+const NVME_LOG_SMART: u32 = 0x02;
+const NVME_LOG_SMART_SIZE: usize = 512;
+const NVME_SMART_TEMPERATURE_OFFSET: usize = 1;
+/// Pause between two readings of the composite temperature
+const NVME_THERMAL_POLL_NS: u64 = 5_000_000_000;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// I/O server channel, where the firmware trust anchors come from
+struct IoIpc(IpcChannel);
+
+impl Transport for IoIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Thermal server channel, where the drive reports its temperature
+struct ThermalIpc(IpcChannel);
+
+impl orion_thermal::Transport for ThermalIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Composite temperature of the drive as a thermal zone
+struct ThermalReporter {
+    sensor: ThermalSensor,
+    channel: ThermalIpc,
+    next_poll_ns: u64,
+}
+
 struct SmartMonitor {
     smart_data: BTreeMap<u8, Vec<u8>>,
     health_status: SmartHealthStatus,
@@ -86,7 +195,6 @@ struct PerformanceMonitor {
     write_operations: u64,
     total_bytes_read: u64,
     total_bytes_written: u64,
-    average_latency: u64,
     peak_bandwidth: u64,
 }
 
@@ -285,6 +393,12 @@ pub struct NvmeLbaFormat {
      interrupt_coalescing: Option<InterruptCoalescing>,
      fabric_manager: Option<FabricManager>,
      zoned_manager: Option<ZonedManager>,
+     io_stats: BlockStatistics,
+     discard_batcher: Option<DiscardBatcher>,
+     write_cache: DeviceCache,
+     firmware_updater: FirmwareUpdater,
+     thermal: Option<ThermalReporter>,
+     watchdog: Watchdog,
  }
 
  struct NvmeIoQueue {
@@ -428,6 +542,13 @@ impl NvmeDriver {
              interrupt_coalescing: None,
              fabric_manager: None,
              zoned_manager: None,
+             io_stats: BlockStatistics::new(),
+             discard_batcher: None,
+             // Assume a volatile cache until the controller says otherwise
+             write_cache: DeviceCache { volatile: true, fua: true, queued_flush: true },
+             firmware_updater: FirmwareUpdater::new(),
+             thermal: None,
+             watchdog: Watchdog::new(TimeoutPolicy::DEFAULT),
          }
      }
 
@@ -530,6 +651,16 @@ impl NvmeDriver {
         };
         
         self.controller_info = Some(controller_info);
+
+        // Discard and cache support live past the end of the decoded structure
+        let (oncs, vwc) = unsafe {
+            (
+                core::ptr::read_volatile(identify_buffer.add(NVME_ID_ONCS_OFFSET) as *const u16),
+                core::ptr::read_volatile(identify_buffer.add(NVME_ID_VWC_OFFSET)),
+            )
+        };
+        self.write_cache.volatile = (vwc & NVME_VWC_PRESENT) != 0;
+        self.discard_batcher = ((oncs & NVME_ONCS_DSM) != 0).then(|| DiscardBatcher::new(DiscardLimits::NVME_DSM));
         Ok(())
     }
 
@@ -648,6 +779,10 @@ impl NvmeDriver {
      }
 
      async fn initialize_io_queues(&mut self) -> DriverResult<()> {
+        self.create_io_queues()
+    }
+
+    fn create_io_queues(&mut self) -> DriverResult<()> {
         // Create I/O queues
         for i in 0..4 { // Create 4 I/O queues
             let queue_id = (i + 1) as u16;
@@ -717,7 +852,8 @@ impl NvmeDriver {
 
      async fn read_blocks_nvme(&mut self, lba: u64, count: u32, buffer: &mut [u8]) -> DriverResult<usize> {
         if !self.device_ready || self.io_queues.is_empty() {
-            return Err(DriverError::IoError);
+            // Also once the watchdog has given the controller up
+            return Err(DriverError::DeviceNotReady);
         }
         
         let queue = &mut self.io_queues[0]; // Use first I/O queue
@@ -745,11 +881,7 @@ impl NvmeDriver {
         self.submit_io_command(queue, &command)?;
         
         // Wait for completion
-        let completion = self.wait_for_io_completion(queue, command_id)?;
-        
-        if (completion.status & 0xFFFE) != 0 {
-            return Err(DriverError::IoError);
-        }
+        self.wait_for_io(command_id)?;
         
         // Update performance metrics
         if let Some(perf_mon) = &mut self.performance_monitor {
@@ -760,9 +892,9 @@ impl NvmeDriver {
         Ok((count * self.block_size) as usize)
     }
 
-         async fn write_blocks_nvme(&mut self, lba: u64, count: u32, buffer: &[u8]) -> DriverResult<usize> {
+         async fn write_blocks_nvme(&mut self, lba: u64, count: u32, buffer: &[u8], fua: bool) -> DriverResult<usize> {
         if !self.device_ready || self.io_queues.is_empty() {
-            return Err(DriverError::IoError);
+            return Err(DriverError::DeviceNotReady);
         }
         
         let queue = &mut self.io_queues[0]; // Use first I/O queue
@@ -780,7 +912,7 @@ impl NvmeDriver {
             data_ptr: buffer.as_ptr() as u64,
             cdw10: lba as u32, // Starting LBA low
             cdw11: (lba >> 32) as u32, // Starting LBA high
-            cdw12: (count - 1) as u32, // Number of logical blocks
+            cdw12: (count - 1) as u32 | if fua { NVME_RW_FUA } else { 0 }, // Number of logical blocks
             cdw13: 0,
             cdw14: 0,
             cdw15: 0,
@@ -790,11 +922,7 @@ impl NvmeDriver {
         self.submit_io_command(queue, &command)?;
         
         // Wait for completion
-        let completion = self.wait_for_io_completion(queue, command_id)?;
-        
-        if (completion.status & 0xFFFE) != 0 {
-            return Err(DriverError::IoError);
-        }
+        self.wait_for_io(command_id)?;
         
         // Update performance metrics
         if let Some(perf_mon) = &mut self.performance_monitor {
@@ -834,38 +962,530 @@ impl NvmeDriver {
         Ok(())
     }
 
-    fn wait_for_io_completion(&mut self, queue: &mut NvmeIoQueue, command_id: u16) -> DriverResult<NvmeCompletion> {
-        let mut timeout = 1000000;
+    /// Take the completion of `command_id` if it is next on the queue
+    fn poll_io_completion(queue: &mut NvmeIoQueue, command_id: u16) -> Option<NvmeCompletion> {
+        let completion = unsafe {
+            core::ptr::read_volatile(queue.completion_queue.add(queue.current_cq_head as usize))
+        };
+        if completion.command_id != command_id {
+            return None;
+        }
         
-        while timeout > 0 {
-            // Check completion queue
-            let cq_ptr = queue.completion_queue;
-            let completion = unsafe {
-                core::ptr::read_volatile(cq_ptr.add(queue.current_cq_head as usize))
-            };
-            
-            if completion.command_id == command_id {
-                // Update head pointer
-                queue.current_cq_head = (queue.current_cq_head + 1) % queue.queue_size;
-                
-                // Ring completion queue doorbell
-                let cq_doorbell = unsafe { queue.doorbell.add(1) };
-                unsafe {
-                    core::ptr::write_volatile(cq_doorbell, queue.current_cq_head);
+        // Update head pointer and ring the completion queue doorbell
+        queue.current_cq_head = (queue.current_cq_head + 1) % queue.queue_size;
+        unsafe {
+            core::ptr::write_volatile(queue.doorbell.add(1), queue.current_cq_head);
+        }
+        Some(completion)
+    }
+
+    /// Wait for I/O command `command_id` on the first queue. Past its
+    /// deadline the command is aborted, then the controller reset, then
+    /// the controller given up (see orion_blkio::timeout).
+    fn wait_for_io(&mut self, command_id: u16) -> DriverResult<()> {
+        let id = command_id as u64;
+        self.watchdog.start(id, monotonic_ns());
+        
+        loop {
+            if let Some(completion) = Self::poll_io_completion(&mut self.io_queues[0], command_id) {
+                if self.watchdog.finish(id) {
+                    return Err(DriverError::Timeout);
                 }
-                
-                return Ok(completion);
+                return completion_result(completion.status);
             }
             
-            timeout -= 1;
-            
-            // Add delay
-            for _ in 0..1000 {
-                core::hint::spin_loop();
+            match self.watchdog.poll(monotonic_ns()) {
+                Some(Recovery::Abort(id)) => {
+                    if self.abort_io(id as u16).is_err() {
+                        self.watchdog.abort_failed(id);
+                    }
+                }
+                Some(Recovery::Reset) => {
+                    // Every command in flight is gone with the old queues
+                    let reset = self.reset_controller();
+                    self.watchdog.reset_done(reset.is_ok());
+                    if reset.is_err() {
+                        self.device_ready = false;
+                    }
+                    return Err(DriverError::Timeout);
+                }
+                Some(Recovery::Offline) => {
+                    self.watchdog.abandon();
+                    self.device_ready = false;
+                    return Err(DriverError::DeviceNotReady);
+                }
+                None => core::hint::spin_loop(),
             }
         }
-        
-        Err(DriverError::Timeout)
+    }
+
+    /// Ask the controller to abort an I/O command of the first queue
+    fn abort_io(&mut self, command_id: u16) -> DriverResult<()> {
+        let queue_id = self.io_queues.first().map(|queue| queue.queue_id).ok_or(DriverError::IoError)?;
+        let cdw10 = queue_id as u32 | (command_id as u32) << 16;
+        match self.admin_command(NVME_ADMIN_ABORT, NVME_ABORT_COMMAND_ID, 0, cdw10, 0)? {
+            0 => Ok(()),
+            _ => Err(DriverError::DeviceError),
+        }
+    }
+
+    /// Disable and re-enable the controller, then rebuild the I/O queues
+    fn reset_controller(&mut self) -> DriverResult<()> {
+        self.io_queues.clear();
+        self.initialize_controller()
+            .and_then(|_| self.identify_controller())
+            .and_then(|_| self.create_io_queues())
+    }
+}
+
+// ========================================
+// DISCARD AND WRITE ORDERING
+// ========================================
+
+/// Error of a failed completion; media errors are kept apart from the
+/// rest, and a command aborted on request has timed out
+fn completion_result(status: u16) -> DriverResult<()> {
+    let code = (status >> NVME_STATUS_SC_SHIFT) & NVME_STATUS_SC_MASK;
+    match ((status >> NVME_STATUS_SCT_SHIFT) & NVME_STATUS_SCT_MASK, code) {
+        (NVME_SCT_GENERIC, 0) => Ok(()),
+        (NVME_SCT_GENERIC, NVME_SC_ABORT_REQUESTED) => Err(DriverError::Timeout),
+        (NVME_SCT_MEDIA, _) => Err(DriverError::IoError),
+        _ => Err(DriverError::DeviceError),
+    }
+}
+
+/// How a driver error is reported to the barrier queue and control callers
+fn io_status(error: &DriverError) -> IoStatus {
+    match error {
+        DriverError::IoError => IoStatus::MediaError,
+        DriverError::Timeout => IoStatus::Timeout,
+        DriverError::DeviceNotReady => IoStatus::Offline,
+        _ => IoStatus::DeviceError,
+    }
+}
+
+/// Data stage of a request going through the barrier queue
+#[derive(Clone, Copy)]
+enum NvmeIo<'a> {
+    Write { lba: u64, count: u32, buffer: &'a [u8] },
+    Discard { ranges: &'a [DiscardRange] },
+    Flush,
+}
+
+impl NvmeDriver {
+    /// Write `count` blocks honouring REQ_PREFLUSH and REQ_FUA. Pending
+    /// discards of the range are dropped, and the others are sent ahead of
+    /// a preflush so they stay ordered before the barrier.
+    pub async fn write_blocks_ordered(&mut self, lba: u64, count: u32, buffer: &[u8], flags: u8) -> DriverResult<usize> {
+        if !self.device_ready {
+            return Err(DriverError::IoError);
+        }
+
+        if buffer.len() < (count * self.block_size) as usize {
+            return Err(DriverError::IoError);
+        }
+
+        if let Some(batcher) = &mut self.discard_batcher {
+            batcher.cancel(lba, count as u64);
+        }
+        if (flags & REQ_PREFLUSH) != 0 {
+            self.send_discards().await?;
+        }
+
+        let start = monotonic_ns();
+        let result = self
+            .run_ordered(Request { op: IoOp::Write, flags, tag: NvmeIo::Write { lba, count, buffer } })
+            .await
+            .map(|_| (count * self.block_size) as usize);
+        self.record_io(Operation::Write, start, &result);
+        result
+    }
+
+    /// Queue a discard of `count` blocks at `lba`. Discards are sent once a
+    /// full Dataset Management command is pending, or by the next flush.
+    pub async fn discard(&mut self, lba: u64, count: u64) -> DriverResult<()> {
+        if !self.device_ready {
+            return Err(DriverError::IoError);
+        }
+
+        if lba.checked_add(count).map_or(true, |end| end > self.block_count) {
+            return Err(DriverError::InvalidParameter);
+        }
+
+        let batcher = self.discard_batcher.as_mut().ok_or(DriverError::Unsupported)?;
+        batcher.add(lba, count);
+        if batcher.is_full() {
+            self.send_discards().await?;
+        }
+        Ok(())
+    }
+
+    /// Serve a raw disk control request (see orion_blkio::control)
+    pub async fn block_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let block_size = self.block_size;
+        let result = match ControlRequest::decode(request, block_size) {
+            None => Err(STATUS_EINVAL),
+            Some(ControlRequest::Info) => {
+                Ok(encode_info(&DiskInfo { block_size, flags: 0, blocks: self.block_count }))
+            }
+            Some(ControlRequest::Read { lba, count }) => {
+                let mut buffer = vec![0u8; (count * block_size) as usize];
+                match self.read_blocks(lba, count, &mut buffer).await {
+                    Ok(_) => Ok(buffer),
+                    Err(error) => Err(io_status(&error).errno()),
+                }
+            }
+            Some(ControlRequest::Write { lba, data }) => {
+                let count = data.len() as u32 / block_size;
+                self.write_blocks(lba, count, data).await.map(|_| Vec::new()).map_err(|error| io_status(&error).errno())
+            }
+            Some(ControlRequest::Flush) => {
+                self.flush().await.map(|_| Vec::new()).map_err(|error| io_status(&error).errno())
+            }
+            Some(ControlRequest::ReadIntegrity { lba, count, algorithm }) => {
+                let mut buffer = vec![0u8; (count * block_size) as usize];
+                match self.read_blocks(lba, count, &mut buffer).await {
+                    Ok(_) => Ok(encode_integrity_read(algorithm, block_size, &buffer)),
+                    Err(error) => Err(io_status(&error).errno()),
+                }
+            }
+            Some(ControlRequest::WriteIntegrity { lba, checksums, data }) => {
+                // Last boundary before the device: nothing damaged gets written
+                match checksums.verify(data) {
+                    Ok(()) => {
+                        let count = data.len() as u32 / block_size;
+                        self.write_blocks(lba, count, data)
+                            .await
+                            .map(|_| Vec::new())
+                            .map_err(|error| io_status(&error).errno())
+                    }
+                    Err(_) => Err(STATUS_EBADMSG),
+                }
+            }
+        };
+        match result {
+            Ok(payload) => reply(STATUS_OK, &payload),
+            Err(status) => reply(status, &[]),
+        }
+    }
+
+    /// Make every completed write durable, after sending pending discards
+    pub async fn flush(&mut self) -> DriverResult<()> {
+        if !self.device_ready {
+            return Err(DriverError::IoError);
+        }
+
+        self.send_discards().await?;
+        let start = monotonic_ns();
+        let result = self.run_ordered(Request { op: IoOp::Flush, flags: 0, tag: NvmeIo::Flush }).await.map(|_| 0);
+        self.record_io(Operation::Flush, start, &result);
+        result.map(|_| ())
+    }
+
+    /// Send the pending discards as Dataset Management commands
+    async fn send_discards(&mut self) -> DriverResult<()> {
+        let commands = match &mut self.discard_batcher {
+            Some(batcher) => batcher.take_commands(),
+            None => return Ok(()),
+        };
+
+        for ranges in commands {
+            let bytes = ranges.iter().map(|range| range.count).sum::<u64>() * self.block_size as u64;
+            let start = monotonic_ns();
+            let result = self
+                .run_ordered(Request { op: IoOp::Discard, flags: 0, tag: NvmeIo::Discard { ranges: &ranges } })
+                .await
+                .map(|_| bytes as usize);
+            self.record_io(Operation::Trim, start, &result);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Run one request through the barrier queue. Each command completes
+    /// before the next is issued, so requests stay in submission order and
+    /// the queue only adds the flushes the request and the cache call for.
+    async fn run_ordered(&mut self, request: Request<NvmeIo<'_>>) -> DriverResult<()> {
+        let mut queue = BarrierQueue::new(self.write_cache);
+        let mut error = DriverError::IoError;
+        queue.submit(request);
+
+        while let Some(command) = queue.next_command() {
+            let (id, result) = match command {
+                Command::Io { id, tag, fua, .. } => (id, self.issue_io(tag, fua).await),
+                Command::Flush { id } => (id, self.issue_io(NvmeIo::Flush, false).await),
+            };
+            queue.complete(id, result.as_ref().map_or_else(io_status, |_| IoStatus::Ok));
+            if let Err(e) = result {
+                error = e;
+            }
+        }
+
+        match queue.take_completed().first() {
+            Some((_, IoStatus::Ok)) => Ok(()),
+            _ => Err(error),
+        }
+    }
+
+    async fn issue_io(&mut self, io: NvmeIo<'_>, fua: bool) -> DriverResult<()> {
+        match io {
+            NvmeIo::Write { lba, count, buffer } => self.write_blocks_nvme(lba, count, buffer, fua).await.map(|_| ()),
+            NvmeIo::Discard { ranges } => {
+                // Range list stays alive until the command completes below
+                let payload = encode_nvme_dsm(ranges);
+                self.io_command(NVME_CMD_DSM, payload.as_ptr() as u64, (ranges.len() - 1) as u32, NVME_DSM_DEALLOCATE)
+            }
+            NvmeIo::Flush => self.io_command(NVME_CMD_FLUSH, 0, 0, 0),
+        }
+    }
+
+    /// Submit an I/O command without a block transfer and wait for it
+    fn io_command(&mut self, opcode: u8, data_ptr: u64, cdw10: u32, cdw11: u32) -> DriverResult<()> {
+        if self.io_queues.is_empty() {
+            return Err(DriverError::IoError);
+        }
+
+        let queue = &mut self.io_queues[0];
+        let command_id = self.get_next_command_id(queue)?;
+        let command = NvmeCommand {
+            opcode,
+            flags: 0,
+            command_id,
+            namespace_id: 1,
+            cdw2: 0,
+            cdw3: 0,
+            metadata_ptr: 0,
+            data_ptr,
+            cdw10,
+            cdw11,
+            cdw12: 0,
+            cdw13: 0,
+            cdw14: 0,
+            cdw15: 0,
+        };
+
+        self.submit_io_command(queue, &command)?;
+        self.wait_for_io(command_id)
+    }
+}
+
+// ========================================
+// FIRMWARE UPDATE
+// ========================================
+
+impl NvmeDriver {
+    /// Submit an admin command and wait for it, returning the status field
+    fn admin_command(&mut self, opcode: u8, command_id: u16, data_ptr: u64, cdw10: u32, cdw11: u32) -> DriverResult<u32> {
+        let command = NvmeCommand {
+            opcode,
+            flags: 0,
+            command_id,
+            namespace_id: 0,
+            cdw2: 0,
+            cdw3: 0,
+            metadata_ptr: 0,
+            data_ptr,
+            cdw10,
+            cdw11,
+            cdw12: 0,
+            cdw13: 0,
+            cdw14: 0,
+            cdw15: 0,
+        };
+
+        self.submit_admin_command(&command)?;
+        let completion = self.wait_for_admin_completion(command_id)?;
+        Ok(completion.status & 0xFFFE)
+    }
+
+    /// Active slot and the revision of every slot, from the firmware slot log
+    fn firmware_slot_log(&mut self) -> DriverResult<(u8, [[u8; 8]; 7])> {
+        let mut log = vec![0u8; NVME_LOG_FW_SLOT_SIZE];
+        let numd = (NVME_LOG_FW_SLOT_SIZE / 4 - 1) as u32;
+        let status = self.admin_command(
+            NVME_ADMIN_GET_LOG_PAGE,
+            107,
+            log.as_mut_ptr() as u64,
+            NVME_LOG_FW_SLOT | (numd << 16),
+            0,
+        )?;
+        if status != 0 {
+            return Err(DriverError::IoError);
+        }
+
+        let mut revisions = [[0u8; 8]; 7];
+        for (slot, revision) in revisions.iter_mut().enumerate() {
+            revision.copy_from_slice(&log[8 + slot * 8..16 + slot * 8]);
+        }
+        Ok((log[0] & 0x07, revisions))
+    }
+
+    /// Firmware Commit of `slot`; returns the status code when the
+    /// controller needs a reset to run the image
+    fn firmware_commit(&mut self, slot: u8, action: u32) -> Result<Option<u32>, FwError> {
+        let status = self
+            .admin_command(NVME_ADMIN_FW_COMMIT, 106, 0, slot as u32 | (action << 3), 0)
+            .map_err(|_| FwError::Device)?;
+        let (sct, sc) = ((status >> 9) & 0x7, (status >> 1) & 0xFF);
+        match (sct, sc) {
+            (0, 0) => Ok(None),
+            (NVME_SCT_COMMAND_SPECIFIC, NVME_SC_FW_NEEDS_CONVENTIONAL_RESET)
+            | (NVME_SCT_COMMAND_SPECIFIC, NVME_SC_FW_NEEDS_SUBSYSTEM_RESET)
+            | (NVME_SCT_COMMAND_SPECIFIC, NVME_SC_FW_NEEDS_RESET) => Ok(Some(sc)),
+            _ => Err(FwError::Device),
+        }
+    }
+
+    /// Reset the controller so it runs the committed image, then rebuild
+    /// the I/O queues. Pending discards are kept and sent afterwards.
+    fn reset_for_firmware(&mut self, subsystem: bool) -> Result<(), FwError> {
+        if subsystem {
+            self.write_register(NVME_NSSR_OFFSET, NVME_NSSR_RESET);
+        }
+        self.reset_controller().map_err(|_| FwError::Device)
+    }
+
+    /// Roll back an update left unconfirmed past its deadline
+    fn check_firmware_deadline(&mut self) {
+        let mut updater = core::mem::take(&mut self.firmware_updater);
+        let _ = updater.tick(self, monotonic_ns());
+        self.firmware_updater = updater;
+    }
+
+    /// Serve a firmware control request (see orion_fwupdate::handle_control)
+    pub fn firmware_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let mut updater = core::mem::take(&mut self.firmware_updater);
+        let reply = handle_control(&mut updater, self, request, monotonic_ns());
+        self.firmware_updater = updater;
+        reply
+    }
+}
+
+// ========================================
+// THERMAL REPORTING
+// ========================================
+
+impl NvmeDriver {
+    /// Composite temperature in kelvins, from the SMART / Health log
+    fn smart_temperature(&mut self) -> DriverResult<u16> {
+        let mut log = vec![0u8; NVME_LOG_SMART_SIZE];
+        let numd = (NVME_LOG_SMART_SIZE / 4 - 1) as u32;
+        let status = self.admin_command(
+            NVME_ADMIN_GET_LOG_PAGE,
+            108,
+            log.as_mut_ptr() as u64,
+            NVME_LOG_SMART | (numd << 16),
+            0,
+        )?;
+        if status != 0 {
+            return Err(DriverError::IoError);
+        }
+        let offset = NVME_SMART_TEMPERATURE_OFFSET;
+        Ok(u16::from_le_bytes([log[offset], log[offset + 1]]))
+    }
+
+    /// Report the temperature to the thermal server every few seconds.
+    /// The zone is named after the serial number so it keeps its name,
+    /// and the trips come from the warning and critical composite
+    /// temperature thresholds of the controller.
+    fn report_temperature(&mut self) {
+        let now = monotonic_ns();
+        if !self.device_ready || self.thermal.as_ref().is_some_and(|thermal| now < thermal.next_poll_ns) {
+            return;
+        }
+        if self.thermal.is_none() {
+            let (serial, warning, critical) = match self.controller_info.as_ref() {
+                Some(info) => (info.sn, { info.wctemp }, { info.cctemp }),
+                None => return,
+            };
+            let serial: String = serial.iter().filter(|byte| byte.is_ascii_alphanumeric()).map(|&byte| byte as char).collect();
+            let name = format!("nvme-{}", &serial[serial.len().saturating_sub(11)..]);
+            let threshold = |kelvin: u16| if kelvin == 0 { 0 } else { kelvin_to_mc(kelvin) };
+            self.thermal = Some(ThermalReporter {
+                sensor: ThermalSensor::new(&name, threshold(warning), threshold(critical)),
+                channel: ThermalIpc(IpcChannel::connect("thermal")),
+                next_poll_ns: 0,
+            });
+        }
+
+        let temperature = self.smart_temperature();
+        if let Some(thermal) = self.thermal.as_mut() {
+            thermal.next_poll_ns = now + NVME_THERMAL_POLL_NS;
+            // 0 means the controller does not report the temperature
+            if let Ok(kelvin @ 1..) = temperature {
+                thermal.sensor.report(&mut thermal.channel, kelvin_to_mc(kelvin), now);
+            }
+        }
+    }
+}
+
+impl FirmwareDevice for NvmeDriver {
+    fn identity(&self) -> (u16, u16) {
+        (self.device.vendor_id, self.device.device_id)
+    }
+
+    fn firmware_info(&mut self) -> Result<FirmwareInfo, FwError> {
+        let controller = self.controller_info.ok_or(FwError::Device)?;
+        let slots = (controller.frmw >> 1) & 0x07;
+        if !self.device_ready || (controller.oacs & NVME_OACS_FW) == 0 || slots == 0 {
+            return Err(FwError::Unsupported);
+        }
+
+        let (active_slot, revisions) = self.firmware_slot_log().map_err(|_| FwError::Device)?;
+        // Stage in a writable slot other than the running one when there is
+        // one, so the running image stays available for a rollback
+        let first_writable = if (controller.frmw & NVME_FRMW_SLOT1_RO) != 0 { 2 } else { 1 };
+        let staging_slot = (first_writable..=slots).find(|&slot| slot != active_slot);
+
+        let mut version = [0u8; 16];
+        if (1..=7).contains(&active_slot) {
+            version[..8].copy_from_slice(&revisions[active_slot as usize - 1]);
+        } else {
+            version[..8].copy_from_slice(&controller.fr);
+        }
+        Ok(FirmwareInfo {
+            version,
+            active_slot,
+            staging_slot: staging_slot.unwrap_or(active_slot),
+            can_rollback: staging_slot.is_some(),
+            max_size: NVME_FW_MAX_IMAGE,
+        })
+    }
+
+    fn write_slot(&mut self, slot: u8, payload: &[u8]) -> Result<(), FwError> {
+        // Download offsets and sizes have to be multiples of the update
+        // granularity; 0xFF means dword granularity
+        let granularity = match self.controller_info.map_or(0, |controller| controller.fwug) {
+            0xFF => 4,
+            0 => NVME_FW_GRANULARITY_UNIT,
+            units => units as usize * NVME_FW_GRANULARITY_UNIT,
+        };
+        let chunk_size = (self.max_transfer_size as usize / granularity).max(1) * granularity;
+
+        for (index, chunk) in payload.chunks(chunk_size).enumerate() {
+            // Pad the last piece to whole dwords
+            let mut data = chunk.to_vec();
+            data.resize((chunk.len() + 3) & !3, 0);
+            let offset = (index * chunk_size / 4) as u32;
+            let numd = (data.len() / 4 - 1) as u32;
+            let status = self
+                .admin_command(NVME_ADMIN_FW_DOWNLOAD, 105, data.as_ptr() as u64, numd, offset)
+                .map_err(|_| FwError::Device)?;
+            if status != 0 {
+                return Err(FwError::Device);
+            }
+        }
+
+        self.firmware_commit(slot, NVME_FW_CA_REPLACE).map(|_| ())
+    }
+
+    fn activate_slot(&mut self, slot: u8) -> Result<(), FwError> {
+        let Some(reset) = self.firmware_commit(slot, NVME_FW_CA_ACTIVATE_NOW)? else {
+            return Ok(());
+        };
+        // The image needs a reset: commit it for the next one and reset now
+        self.firmware_commit(slot, NVME_FW_CA_ACTIVATE_AT_RESET)?;
+        self.reset_for_firmware(reset == NVME_SC_FW_NEEDS_SUBSYSTEM_RESET)
     }
 }
 
@@ -898,6 +1518,9 @@ impl NvmeDriver {
          
          // Identify namespace 1
          self.identify_namespace(1)?;
+
+         // Without trust anchors every firmware image is refused
+         let _ = self.firmware_updater.load_trust_anchors(&mut IoIpc(IpcChannel::connect("io")));
          
          // Initialize I/O queues
          self.initialize_io_queues().await?;
@@ -1028,11 +1651,6 @@ impl NvmeDriver {
              return Err(DriverError::IoError);
          }
          
-         // Update performance metrics
-         if let Some(perf_mon) = &mut self.performance_monitor {
-             perf_mon.average_latency = (perf_mon.average_latency + 1000) / 2; // Example latency
-         }
-         
          Ok(())
      }
 
@@ -1049,11 +1667,6 @@ impl NvmeDriver {
              queue.free_slots.push(command_id);
          }
          
-         // Update performance metrics
-         if let Some(perf_mon) = &mut self.performance_monitor {
-             perf_mon.average_latency = (perf_mon.average_latency + 500) / 2; // Example latency
-         }
-         
          Ok(())
      }
      
@@ -1080,6 +1693,8 @@ impl NvmeDriver {
      }
      
      async fn handle_message(&mut self, message: ReceivedMessage, ipc: &mut dyn IpcInterface) -> DriverResult<()> {
+         self.check_firmware_deadline();
+         self.report_temperature();
          match message {
              ReceivedMessage::ProbeDevice(probe_msg) => {
                  let can_handle = self.can_handle(probe_msg.vendor_id, probe_msg.device_id);
@@ -1102,6 +1717,15 @@ impl NvmeDriver {
                          let bytes_written = self.write_blocks(0, 1, &buffer).await?;
                          Ok(bytes_written)
                      }
+                     orion_driver::IoRequestType::Ioctl if io_msg.length == FW_IOCTL_CONTROL => {
+                         // Firmware requests answer with data rather than a length
+                         let reply = self.firmware_control(&io_msg.data);
+                         return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                     }
+                     orion_driver::IoRequestType::Ioctl if io_msg.length == BLK_IOCTL_CONTROL => {
+                         let reply = self.block_control(&io_msg.data).await;
+                         return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                     }
                      orion_driver::IoRequestType::Ioctl => {
                          // Handle ioctl request
                          Ok(0)
@@ -1140,7 +1764,10 @@ impl NvmeDriver {
              return Err(DriverError::IoError);
          }
          
-         self.read_blocks_nvme(lba, count, buffer).await
+         let start = monotonic_ns();
+         let result = self.read_blocks_nvme(lba, count, buffer).await;
+         self.record_io(Operation::Read, start, &result);
+         result
      }
      
      async fn write_blocks(&mut self, lba: u64, count: u32, buffer: &[u8]) -> DriverResult<usize> {
@@ -1152,7 +1779,7 @@ impl NvmeDriver {
              return Err(DriverError::IoError);
          }
          
-         self.write_blocks_nvme(lba, count, buffer).await
+         self.write_blocks_ordered(lba, count, buffer, 0).await
      }
      
      async fn get_capacity(&self) -> DriverResult<u64> {
@@ -1170,6 +1797,12 @@ impl NvmeDriver {
      }
  }
 
+impl BlockStatsSource for NvmeDriver {
+    fn block_statistics(&self) -> &BlockStatistics {
+        &self.io_stats
+    }
+}
+
 // ========================================
 // DRIVER ENTRY POINT
 // ========================================
@@ -1247,6 +1880,16 @@ impl NvmeDriver {
         if let Some(perf) = &self.performance_monitor {
             status.push(perf.get_performance_report());
         }
+
+        let latency = self.io_stats.report().latency;
+        status.push(format!("  Latency: p50 {} ns, p99 {} ns, p99.9 {} ns, max {} ns",
+            latency.p50, latency.p99, latency.p999, latency.max
+        ));
+        status.push(format!("  Volatile Write Cache: {}", self.write_cache.volatile));
+        if let Some(batcher) = &self.discard_batcher {
+            status.push(format!("  Pending Discard: {} blocks", batcher.pending_blocks()));
+        }
+        status.push(format!("  Firmware Update: {:?}", self.firmware_updater.state()));
         
         if let Some(enc) = &self.encryption_manager {
             status.push(enc.get_encryption_info());
@@ -1279,6 +1922,14 @@ impl NvmeDriver {
         status.join("\n")
     }
 
+    /// Account a finished read or write started at `start` (monotonic ns)
+    fn record_io(&self, operation: Operation, start: u64, result: &DriverResult<usize>) {
+        match result {
+            Ok(bytes) => self.io_stats.record(operation, *bytes as u64, monotonic_ns().saturating_sub(start)),
+            Err(_) => self.io_stats.record_error(operation),
+        }
+    }
+
     /// Execute a SMART self-test
     pub async fn execute_smart_self_test(&mut self, test_type: u8) -> DriverResult<()> {
         if !self.device_ready {
@@ -1316,43 +1967,13 @@ impl NvmeDriver {
         Ok(())
     }
 
-    /// Get device temperature
+    /// Get device temperature in degrees Celsius
     pub async fn get_device_temperature(&mut self) -> DriverResult<u16> {
         if !self.device_ready {
             return Err(DriverError::IoError);
         }
-
-        // Create temperature command
-        let command = NvmeCommand {
-            opcode: 0x0A, // Get Features
-            flags: 0,
-            command_id: 101,
-            namespace_id: 1,
-            cdw2: 0,
-            cdw3: 0,
-            metadata_ptr: 0,
-            data_ptr: 0,
-            cdw10: 0x00000002, // SMART/Health Information
-            cdw11: 0,
-            cdw12: 0,
-            cdw13: 0,
-            cdw14: 0,
-            cdw15: 0,
-        };
-
-        // Submit command to admin queue
-        self.submit_admin_command(&command)?;
-
-        // Wait for completion
-        let completion = self.wait_for_admin_completion(101)?;
-
-        if (completion.status & 0xFFFE) != 0 {
-            return Err(DriverError::IoError);
-        }
-
-        // In a real implementation, this would read temperature from completion data
-        // For now, return a default temperature
-        Ok(35)
+        let kelvin = self.smart_temperature()?;
+        Ok(kelvin.saturating_sub(273))
     }
 
     /// Configure advanced features
@@ -1624,6 +2245,20 @@ mod tests {
         assert_eq!(perf.write_operations, 1);
         assert_eq!(perf.total_bytes_written, 2048);
     }
+
+    #[test]
+    fn test_block_statistics() {
+        let driver = NvmeDriver::new();
+        driver.record_io(Operation::Write, 0, &Ok(8192));
+        driver.record_io(Operation::Read, 0, &Err(DriverError::IoError));
+
+        let report = driver.block_stats_report();
+        assert_eq!((report.write.operations, report.write.bytes), (1, 8192));
+        assert_eq!((report.read.operations, report.read.errors), (0, 1));
+
+        driver.reset_block_stats();
+        assert_eq!(driver.block_stats_report().total_operations(), 0);
+    }
     
     #[test]
     fn test_encryption_manager() {
@@ -25,6 +25,7 @@ use orion_async::{
     sync::{AsyncMutex, AsyncRwLock},
     channel::AsyncChannel,
 };
+use orion_sys::{clock_get, nanosleep};
 use orion_driver::{
     OrionDriver, BlockDriver, DeviceInfo, DriverError, DriverResult,
     MessageLoop, ReceivedMessage, IpcInterface,
@@ -2383,17 +2384,26 @@ pub enum HealthStatusType {
     Critical,
 }
 
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn idle_ns(duration: u64) {
+    let _ = nanosleep(duration);
+}
+
 // Main driver entry point
 #[no_mangle]
 pub extern "C" fn driver_main() -> i32 {
     // Create RAID driver instance
     let mut driver = RaidDriver::new();
     
-    // Run async initialization
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    let result = runtime.block_on(async {
-        driver.initialize().await
-    });
+    // Run async initialization, timers driven by the monotonic clock
+    orion_async::set_clock(monotonic_ns);
+    orion_async::set_idle(idle_ns);
+    let result = orion_async::block_on(driver.initialize());
     
     match result {
         Ok(_) => {
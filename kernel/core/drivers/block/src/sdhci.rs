@@ -0,0 +1,1197 @@
+/*
+ * Orion Operating System - SD Host Controller Driver
+ *
+ * Driver for SD host controllers following the SD Host Controller
+ * Simplified Specification (SDHCI), the SD slot of most ARM boards and of
+ * PC card readers. It brings up SDv1 standard capacity cards as well as
+ * SDHC and SDXC cards on a 4-bit bus, moves data with ADMA2 descriptor
+ * tables and follows card insertion and removal through the controller's
+ * card detect interrupt.
+ *
+ * Controllers are PCI functions on PCs and device tree nodes on ARM
+ * boards; the firmware enumeration hands the driver a node's `compatible`
+ * and `reg` the way it does for I2C devices (see lib/orion_i2c), and the
+ * driver takes it when it lists a controller of the standard layout.
+ *
+ * The card is a block device of 512 byte blocks, served with the raw
+ * disk control requests of orion_blkio like an NVMe or SATA disk, so the
+ * storage stack and the installer see a card as one more disk. While no
+ * card is in the slot every request answers ENODEV; INFO describes the
+ * card that comes in next.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_blkio::control::{
+    encode_info, encode_integrity_read, ControlRequest, DiskInfo, BLK_INFO_READ_ONLY, STATUS_EBADMSG, STATUS_EINVAL,
+    STATUS_EIO, STATUS_ENODEV, STATUS_EROFS, STATUS_ETIMEDOUT, STATUS_OK,
+};
+use orion_blkio::BLK_IOCTL_CONTROL;
+use orion_driver::{
+    BlockDriver, DeviceInfo, DriverError, DriverInfo, DriverResult, MessageLoop, MmioAccessor, MmioPermissions,
+    OrionDriver, ReceivedMessage,
+};
+use orion_proto::reply;
+use orion_sys::nanosleep;
+use orion_virtq::{DmaAllocator, DmaPool, DmaRegion, LeakTracker};
+
+// Global allocator for the driver
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding request and reply buffers
+const HEAP_SIZE: usize = 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+// SD host controllers the driver binds to
+const SDHCI_PCI_IDS: &[(u16, u16)] = &[
+    (0x1B36, 0x0007), // QEMU sdhci-pci
+    (0x1180, 0xE822), // Ricoh R5CE822
+    (0x1180, 0xE823), // Ricoh R5CE823
+    (0x1217, 0x8620), // O2 Micro SD
+    (0x8086, 0x0F16), // Bay Trail SD
+    (0x8086, 0x5ACA), // Apollo Lake SD
+];
+
+// PCI class of SD host controllers (base system peripheral, SD host)
+const PCI_CLASS_SYSTEM: u8 = 0x08;
+const PCI_SUBCLASS_SDHCI: u8 = 0x05;
+
+/// Device tree controllers keeping to the standard register layout
+const SDHCI_FDT_COMPATIBLE: &[&str] =
+    &["arasan,sdhci-8.9a", "arasan,sdhci-5.1", "xlnx,zynqmp-8.9a", "snps,dwcmshc-sdhci", "brcm,bcm2711-emmc2"];
+
+/// Ioctl through which the firmware enumeration hands over a device tree
+/// node: its `compatible` and `reg`, each a `len: u32` and the raw bytes,
+/// then the parent's `#address-cells` and `#size-cells`, both u32
+pub const SDHCI_IOCTL_FDT_NODE: u64 = 0x3030;
+
+const SDHCI_WINDOW_SIZE: usize = 0x100;
+
+// Registers, accessed as the 32-bit words they are packed in
+const SDHCI_BLOCK: u64 = 0x04; // block size, block count
+const SDHCI_ARGUMENT: u64 = 0x08;
+const SDHCI_COMMAND: u64 = 0x0C; // transfer mode, command
+const SDHCI_RESPONSE: u64 = 0x10;
+const SDHCI_PRESENT_STATE: u64 = 0x24;
+const SDHCI_HOST_CONTROL: u64 = 0x28; // host control, power, block gap, wakeup
+const SDHCI_CLOCK_CONTROL: u64 = 0x2C; // clock, timeout, software reset
+const SDHCI_INT_STATUS: u64 = 0x30; // normal, error
+const SDHCI_INT_ENABLE: u64 = 0x34;
+const SDHCI_SIGNAL_ENABLE: u64 = 0x38;
+const SDHCI_CAPABILITIES: u64 = 0x40;
+const SDHCI_ADMA_ADDRESS: u64 = 0x58;
+const SDHCI_ADMA_ADDRESS_HIGH: u64 = 0x5C;
+const SDHCI_HOST_VERSION: u64 = 0xFC;
+
+// PRESENT_STATE
+const PRESENT_CMD_INHIBIT: u32 = 1 << 0;
+const PRESENT_DAT_INHIBIT: u32 = 1 << 1;
+const PRESENT_CARD_INSERTED: u32 = 1 << 16;
+const PRESENT_WRITE_ENABLED: u32 = 1 << 19;
+
+// HOST_CONTROL word
+const HOST_4BIT_BUS: u32 = 1 << 1;
+const HOST_DMA_MASK: u32 = 3 << 3;
+const HOST_ADMA2_32: u32 = 2 << 3;
+const HOST_ADMA2_64: u32 = 3 << 3;
+const POWER_ON_3V3: u32 = 0x0F << 8;
+
+// CLOCK_CONTROL word
+const CLOCK_INTERNAL_ENABLE: u32 = 1 << 0;
+const CLOCK_INTERNAL_STABLE: u32 = 1 << 1;
+const CLOCK_CARD_ENABLE: u32 = 1 << 2;
+const TIMEOUT_MAX: u32 = 0x0E << 16;
+const RESET_ALL: u32 = 1 << 24;
+const RESET_CMD: u32 = 1 << 25;
+const RESET_DAT: u32 = 1 << 26;
+
+// INT_STATUS: normal in the low half, errors in the high half
+const INT_CMD_COMPLETE: u32 = 1 << 0;
+const INT_TRANSFER_COMPLETE: u32 = 1 << 1;
+const INT_CARD_INSERTION: u32 = 1 << 6;
+const INT_CARD_REMOVAL: u32 = 1 << 7;
+const INT_ERROR: u32 = 1 << 15;
+const INT_CMD_TIMEOUT: u32 = 1 << 16;
+const INT_DATA_TIMEOUT: u32 = 1 << 20;
+const INT_CARD_EVENTS: u32 = INT_CARD_INSERTION | INT_CARD_REMOVAL;
+
+// CAPABILITIES
+const CAP_BASE_CLOCK_SHIFT: u32 = 8;
+const CAP_ADMA2: u32 = 1 << 19;
+const CAP_VOLTAGE_3V3: u32 = 1 << 24;
+const CAP_64BIT: u32 = 1 << 28;
+
+/// HOST_VERSION value of a version 3.00 controller, with a 10-bit divider
+const SPEC_VERSION_3: u32 = 2;
+
+// COMMAND: the transfer mode in the low half, the command in the high half
+const MODE_DMA: u32 = 1 << 0;
+const MODE_BLOCK_COUNT: u32 = 1 << 1;
+const MODE_AUTO_CMD12: u32 = 1 << 2;
+const MODE_READ: u32 = 1 << 4;
+const MODE_MULTI_BLOCK: u32 = 1 << 5;
+
+const RESPONSE_NONE: u32 = 0;
+const RESPONSE_136: u32 = 1;
+const RESPONSE_48: u32 = 2;
+const RESPONSE_48_BUSY: u32 = 3;
+const COMMAND_CRC_CHECK: u32 = 1 << 3;
+const COMMAND_INDEX_CHECK: u32 = 1 << 4;
+const COMMAND_DATA: u32 = 1 << 5;
+
+// Response types of the SD commands
+const R1: u32 = RESPONSE_48 | COMMAND_CRC_CHECK | COMMAND_INDEX_CHECK;
+const R1B: u32 = RESPONSE_48_BUSY | COMMAND_CRC_CHECK | COMMAND_INDEX_CHECK;
+const R2: u32 = RESPONSE_136 | COMMAND_CRC_CHECK;
+const R3: u32 = RESPONSE_48;
+const R6: u32 = R1;
+const R7: u32 = R1;
+
+// SD commands; ACMDs follow APP_CMD
+const CMD_GO_IDLE: u32 = 0;
+const CMD_ALL_SEND_CID: u32 = 2;
+const CMD_SEND_RELATIVE_ADDR: u32 = 3;
+const ACMD_SET_BUS_WIDTH: u32 = 6;
+const CMD_SELECT_CARD: u32 = 7;
+const CMD_SEND_IF_COND: u32 = 8;
+const CMD_SEND_CSD: u32 = 9;
+const CMD_SET_BLOCKLEN: u32 = 16;
+const CMD_READ_SINGLE: u32 = 17;
+const CMD_READ_MULTIPLE: u32 = 18;
+const CMD_WRITE_SINGLE: u32 = 24;
+const CMD_WRITE_MULTIPLE: u32 = 25;
+const ACMD_SD_SEND_OP_COND: u32 = 41;
+const CMD_APP_CMD: u32 = 55;
+
+/// SEND_IF_COND argument: 2.7-3.6 V and a check pattern echoed back
+const IF_COND_3V3: u32 = 0x1AA;
+/// SD_SEND_OP_COND: the 3.2-3.4 V window, host supports high capacity,
+/// card powered up, card is high capacity
+const OCR_3V3: u32 = 0x0030_0000;
+const OCR_HCS: u32 = 1 << 30;
+const OCR_READY: u32 = 1 << 31;
+const OCR_CCS: u32 = 1 << 30;
+/// ACMD6 argument for a 4-bit bus
+const BUS_WIDTH_4: u32 = 2;
+/// Error bits of an R1 card status
+const R1_ERRORS: u32 = 0xFDF9_0008;
+
+pub const SD_BLOCK_SIZE: u32 = 512;
+
+// Bus clocks in kHz: identification, then default speed
+const CLOCK_IDENTIFICATION_KHZ: u32 = 400;
+const CLOCK_DEFAULT_SPEED_KHZ: u32 = 25_000;
+
+// ADMA2 descriptors: 32-bit ones are 8 bytes, 64-bit ones 12
+const ADMA2_VALID: u16 = 1 << 0;
+const ADMA2_END: u16 = 1 << 1;
+const ADMA2_TRAN: u16 = 2 << 4;
+/// Data each descriptor moves, a power of two below the 64 KiB limit
+const ADMA2_MAX_LENGTH: usize = 0x8000;
+
+/// Blocks one read or write command moves
+const SDHCI_MAX_BLOCKS: u32 = 128;
+
+// Window the descriptor tables and bounce buffers are taken from
+const SDHCI_DMA_BASE: u64 = 0x0C00_0000;
+const SDHCI_DMA_SIZE: u64 = 0x40000;
+const SDHCI_DMA_ALIGN: usize = 64;
+
+/// Checks of the interrupt status, SDHCI_POLL_NS apart, before a command
+/// or a transfer is given up
+const SDHCI_POLL_NS: u64 = 10_000;
+const SDHCI_COMMAND_POLLS: u32 = 10_000;
+const SDHCI_TRANSFER_POLLS: u32 = 100_000;
+/// A card has a second to finish powering up
+const POWER_UP_POLL_NS: u64 = 10_000_000;
+const POWER_UP_POLLS: u32 = 100;
+
+/// Register access, abstracted so the driver runs against a simulated
+/// controller in the tests
+pub trait SdhciRegisters {
+    fn read(&mut self, register: u64) -> u32;
+    fn write(&mut self, register: u64, value: u32);
+}
+
+impl SdhciRegisters for MmioAccessor {
+    fn read(&mut self, register: u64) -> u32 {
+        self.read_u32(register).unwrap_or(0)
+    }
+
+    fn write(&mut self, register: u64, value: u32) {
+        let _ = self.write_u32(register, value);
+    }
+}
+
+// ========================================
+// CARD
+// ========================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardKind {
+    /// Standard capacity, up to 2 GiB and byte addressed
+    Sdsc,
+    /// High capacity, up to 32 GiB
+    Sdhc,
+    /// Extended capacity, above 32 GiB
+    Sdxc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Card {
+    pub kind: CardKind,
+    pub rca: u16,
+    pub blocks: u64,
+    pub read_only: bool,
+}
+
+/// Bits `high..=low` of a CSD register
+fn csd_bits(csd: u128, high: u32, low: u32) -> u64 {
+    ((csd >> low) & ((1u128 << (high - low + 1)) - 1)) as u64
+}
+
+/// Kind and size in blocks of the card a CSD describes
+fn parse_csd(csd: u128) -> Option<(CardKind, u64)> {
+    match csd_bits(csd, 127, 126) {
+        0 => {
+            let read_bl_len = csd_bits(csd, 83, 80);
+            let c_size = csd_bits(csd, 73, 62);
+            let c_size_mult = csd_bits(csd, 49, 47);
+            let bytes = (c_size + 1) << (c_size_mult + 2 + read_bl_len);
+            Some((CardKind::Sdsc, bytes / SD_BLOCK_SIZE as u64))
+        }
+        1 => {
+            // Counted in 512 KiB units
+            let c_size = csd_bits(csd, 69, 48);
+            let kind = if c_size > 0xFFFF { CardKind::Sdxc } else { CardKind::Sdhc };
+            Some((kind, (c_size + 1) * 1024))
+        }
+        _ => None,
+    }
+}
+
+// ========================================
+// CONTROLLER
+// ========================================
+
+pub struct Sdhci<R: SdhciRegisters, A: DmaAllocator> {
+    registers: R,
+    dma: Rc<DmaPool<A>>,
+    version: u32,
+    /// Base clock in kHz
+    base_clock: u32,
+    adma64: bool,
+    card: Option<Card>,
+}
+
+impl<R: SdhciRegisters, A: DmaAllocator> Sdhci<R, A> {
+    /// Reset the controller and bring up the card in the slot, if any
+    pub fn new(mut registers: R, dma: DmaPool<A>) -> DriverResult<Self> {
+        let capabilities = registers.read(SDHCI_CAPABILITIES);
+        if capabilities & CAP_ADMA2 == 0 || capabilities & CAP_VOLTAGE_3V3 == 0 {
+            return Err(DriverError::Unsupported);
+        }
+        let version = (registers.read(SDHCI_HOST_VERSION) >> 16) & 0xFF;
+        let base_clock = ((capabilities >> CAP_BASE_CLOCK_SHIFT) & 0xFF) * 1000;
+        if base_clock == 0 {
+            return Err(DriverError::Unsupported);
+        }
+        let mut controller = Self {
+            registers,
+            dma: Rc::new(dma),
+            version,
+            base_clock,
+            adma64: capabilities & CAP_64BIT != 0,
+            card: None,
+        };
+        controller.reset(RESET_ALL)?;
+        controller.registers.write(SDHCI_INT_ENABLE, 0xFFFF_FFFF);
+        controller.registers.write(SDHCI_SIGNAL_ENABLE, INT_CARD_EVENTS);
+        controller.detect();
+        Ok(controller)
+    }
+
+    pub fn card(&self) -> Option<Card> {
+        self.card
+    }
+
+    fn reset(&mut self, lines: u32) -> DriverResult<()> {
+        let clock = self.registers.read(SDHCI_CLOCK_CONTROL);
+        self.registers.write(SDHCI_CLOCK_CONTROL, clock | lines);
+        for _ in 0..SDHCI_COMMAND_POLLS {
+            if self.registers.read(SDHCI_CLOCK_CONTROL) & lines == 0 {
+                return Ok(());
+            }
+            let _ = nanosleep(SDHCI_POLL_NS);
+        }
+        Err(DriverError::Timeout)
+    }
+
+    /// Divider field of the clock control for a bus clock of at most
+    /// `khz`: 10 bits dividing by twice their value on version 3.00
+    /// controllers, a power of two below 256 before
+    fn clock_divider(&self, khz: u32) -> u32 {
+        if self.base_clock <= khz {
+            return 0;
+        }
+        if self.version >= SPEC_VERSION_3 {
+            let divider = self.base_clock.div_ceil(2 * khz).min(0x3FF);
+            return (divider & 0xFF) << 8 | (divider >> 8) << 6;
+        }
+        let mut divisor = 2;
+        while divisor < 256 && self.base_clock / divisor > khz {
+            divisor *= 2;
+        }
+        (divisor / 2) << 8
+    }
+
+    fn set_clock(&mut self, khz: u32) -> DriverResult<()> {
+        // The bus clock stops while the divider changes
+        self.registers.write(SDHCI_CLOCK_CONTROL, 0);
+        let control = self.clock_divider(khz) | TIMEOUT_MAX | CLOCK_INTERNAL_ENABLE;
+        self.registers.write(SDHCI_CLOCK_CONTROL, control);
+        let mut stable = false;
+        for _ in 0..SDHCI_COMMAND_POLLS {
+            if self.registers.read(SDHCI_CLOCK_CONTROL) & CLOCK_INTERNAL_STABLE != 0 {
+                stable = true;
+                break;
+            }
+            let _ = nanosleep(SDHCI_POLL_NS);
+        }
+        if !stable {
+            return Err(DriverError::Timeout);
+        }
+        self.registers.write(SDHCI_CLOCK_CONTROL, control | CLOCK_CARD_ENABLE);
+        Ok(())
+    }
+
+    /// Wait until every bit of `mask` is set in the interrupt status and
+    /// clear them; an error resets the command and data lines
+    fn wait_status(&mut self, mask: u32, polls: u32) -> DriverResult<()> {
+        for _ in 0..polls {
+            let status = self.registers.read(SDHCI_INT_STATUS);
+            if status & INT_ERROR != 0 {
+                // Card events stay for the interrupt handler
+                self.registers.write(SDHCI_INT_STATUS, status & !INT_CARD_EVENTS);
+                self.reset(RESET_CMD | RESET_DAT)?;
+                return Err(if status & (INT_CMD_TIMEOUT | INT_DATA_TIMEOUT) != 0 {
+                    DriverError::Timeout
+                } else {
+                    DriverError::IoError
+                });
+            }
+            if status & mask == mask {
+                self.registers.write(SDHCI_INT_STATUS, mask);
+                return Ok(());
+            }
+            let _ = nanosleep(SDHCI_POLL_NS);
+        }
+        self.reset(RESET_CMD | RESET_DAT)?;
+        Err(DriverError::Timeout)
+    }
+
+    /// Send a command and return its response, waiting for the data or
+    /// busy phase too; data commands have their block count and
+    /// descriptor table set up already
+    fn command(&mut self, index: u32, argument: u32, flags: u32, mode: u32) -> DriverResult<[u32; 4]> {
+        let inhibit = if flags & COMMAND_DATA != 0 || flags & 3 == RESPONSE_48_BUSY {
+            PRESENT_CMD_INHIBIT | PRESENT_DAT_INHIBIT
+        } else {
+            PRESENT_CMD_INHIBIT
+        };
+        let mut idle = false;
+        for _ in 0..SDHCI_COMMAND_POLLS {
+            if self.registers.read(SDHCI_PRESENT_STATE) & inhibit == 0 {
+                idle = true;
+                break;
+            }
+            let _ = nanosleep(SDHCI_POLL_NS);
+        }
+        if !idle {
+            self.reset(RESET_CMD | RESET_DAT)?;
+            return Err(DriverError::Busy);
+        }
+        self.registers.write(SDHCI_INT_STATUS, !INT_CARD_EVENTS);
+        self.registers.write(SDHCI_ARGUMENT, argument);
+        self.registers.write(SDHCI_COMMAND, (index << 8 | flags) << 16 | mode);
+        self.wait_status(INT_CMD_COMPLETE, SDHCI_COMMAND_POLLS)?;
+        let mut response = [0; 4];
+        for (word, value) in response.iter_mut().enumerate() {
+            *value = self.registers.read(SDHCI_RESPONSE + 4 * word as u64);
+        }
+        if flags & COMMAND_DATA != 0 || flags & 3 == RESPONSE_48_BUSY {
+            self.wait_status(INT_TRANSFER_COMPLETE, SDHCI_TRANSFER_POLLS)?;
+        }
+        Ok(response)
+    }
+
+    fn app_command(&mut self, index: u32, argument: u32, flags: u32) -> DriverResult<[u32; 4]> {
+        let rca = self.card.map_or(0, |card| card.rca);
+        self.command(CMD_APP_CMD, (rca as u32) << 16, R1, 0)?;
+        self.command(index, argument, flags, 0)
+    }
+
+    /// Power the slot and identify the card in it
+    fn initialize_card(&mut self) -> DriverResult<Card> {
+        let host = self.registers.read(SDHCI_HOST_CONTROL) & !(HOST_4BIT_BUS | HOST_DMA_MASK | 0xFF00);
+        self.registers.write(SDHCI_HOST_CONTROL, host | POWER_ON_3V3);
+        self.set_clock(CLOCK_IDENTIFICATION_KHZ)?;
+
+        self.command(CMD_GO_IDLE, 0, RESPONSE_NONE, 0)?;
+        // Version 1 cards do not answer SEND_IF_COND
+        let version2 = match self.command(CMD_SEND_IF_COND, IF_COND_3V3, R7, 0) {
+            Ok(response) if response[0] & 0xFFF == IF_COND_3V3 => true,
+            Ok(_) => return Err(DriverError::Unsupported),
+            Err(DriverError::Timeout) => false,
+            Err(error) => return Err(error),
+        };
+        let argument = if version2 { OCR_3V3 | OCR_HCS } else { OCR_3V3 };
+        let mut ocr = 0;
+        for _ in 0..POWER_UP_POLLS {
+            ocr = self.app_command(ACMD_SD_SEND_OP_COND, argument, R3)?[0];
+            if ocr & OCR_READY != 0 {
+                break;
+            }
+            let _ = nanosleep(POWER_UP_POLL_NS);
+        }
+        if ocr & OCR_READY == 0 {
+            return Err(DriverError::Timeout);
+        }
+
+        self.command(CMD_ALL_SEND_CID, 0, R2, 0)?;
+        let rca = (self.command(CMD_SEND_RELATIVE_ADDR, 0, R6, 0)?[0] >> 16) as u16;
+        // The controller strips the CRC: the response is CSD bits 127-8
+        let response = self.command(CMD_SEND_CSD, (rca as u32) << 16, R2, 0)?;
+        let csd = response.iter().rev().fold(0u128, |csd, &word| csd << 32 | word as u128) << 8;
+        let (kind, blocks) = parse_csd(csd).ok_or(DriverError::Unsupported)?;
+        if (kind == CardKind::Sdsc) != (ocr & OCR_CCS == 0) {
+            return Err(DriverError::Unsupported);
+        }
+        let write_protected = csd_bits(csd, 13, 12) != 0;
+        let switch_enabled = self.registers.read(SDHCI_PRESENT_STATE) & PRESENT_WRITE_ENABLED != 0;
+        let card = Card { kind, rca, blocks, read_only: write_protected || !switch_enabled };
+
+        self.command(CMD_SELECT_CARD, (rca as u32) << 16, R1B, 0)?;
+        self.card = Some(card);
+        self.app_command(ACMD_SET_BUS_WIDTH, BUS_WIDTH_4, R1)?;
+        if kind == CardKind::Sdsc {
+            self.command(CMD_SET_BLOCKLEN, SD_BLOCK_SIZE, R1, 0)?;
+        }
+        let dma = if self.adma64 { HOST_ADMA2_64 } else { HOST_ADMA2_32 };
+        self.registers.write(SDHCI_HOST_CONTROL, host | POWER_ON_3V3 | HOST_4BIT_BUS | dma);
+        self.set_clock(CLOCK_DEFAULT_SPEED_KHZ)?;
+        Ok(card)
+    }
+
+    /// Bring up the card in the slot or forget the one that left it
+    fn detect(&mut self) {
+        self.card = None;
+        if self.registers.read(SDHCI_PRESENT_STATE) & PRESENT_CARD_INSERTED == 0 {
+            let host = self.registers.read(SDHCI_HOST_CONTROL);
+            self.registers.write(SDHCI_HOST_CONTROL, host & !POWER_ON_3V3);
+            return;
+        }
+        match self.initialize_card() {
+            Ok(card) => self.card = Some(card),
+            Err(_) => self.card = None,
+        }
+    }
+
+    /// Handle the controller's interrupt: a card came in or left
+    pub fn interrupt(&mut self) {
+        let status = self.registers.read(SDHCI_INT_STATUS) & INT_CARD_EVENTS;
+        if status != 0 {
+            self.registers.write(SDHCI_INT_STATUS, status);
+            self.detect();
+        }
+    }
+
+    /// Move `count` blocks at `lba`, at most SDHCI_MAX_BLOCKS, between the
+    /// card and `data` through a bounce buffer described by an ADMA2 table
+    fn transfer(&mut self, lba: u64, count: u32, data: &mut [u8], read: bool) -> DriverResult<()> {
+        let card = self.card.ok_or(DriverError::DeviceNotFound)?;
+        let length = (count * SD_BLOCK_SIZE) as usize;
+        let dma = self.dma.clone();
+        let buffer = dma.alloc(length).ok_or(DriverError::OutOfMemory)?;
+        let descriptors = adma2_table(buffer.addr(), length, self.adma64);
+        let table = dma.alloc(descriptors.len()).ok_or(DriverError::OutOfMemory)?;
+        if !self.adma64 && (buffer.addr() | table.addr()) >> 32 != 0 {
+            return Err(DriverError::MemoryError);
+        }
+        table.write(0, &descriptors);
+        if !read {
+            buffer.write(0, &data[..length]);
+        }
+
+        self.registers.write(SDHCI_ADMA_ADDRESS, table.addr() as u32);
+        if self.adma64 {
+            self.registers.write(SDHCI_ADMA_ADDRESS_HIGH, (table.addr() >> 32) as u32);
+        }
+        self.registers.write(SDHCI_BLOCK, count << 16 | SD_BLOCK_SIZE);
+        // Standard capacity cards are addressed in bytes
+        let address = if card.kind == CardKind::Sdsc { lba * SD_BLOCK_SIZE as u64 } else { lba };
+        let (index, mode) = match (read, count) {
+            (true, 1) => (CMD_READ_SINGLE, MODE_DMA | MODE_READ),
+            (true, _) => {
+                (CMD_READ_MULTIPLE, MODE_DMA | MODE_READ | MODE_BLOCK_COUNT | MODE_MULTI_BLOCK | MODE_AUTO_CMD12)
+            }
+            (false, 1) => (CMD_WRITE_SINGLE, MODE_DMA),
+            (false, _) => (CMD_WRITE_MULTIPLE, MODE_DMA | MODE_BLOCK_COUNT | MODE_MULTI_BLOCK | MODE_AUTO_CMD12),
+        };
+        let status = match self.command(index, address as u32, R1 | COMMAND_DATA, mode) {
+            Ok(response) => response[0],
+            // The card left the slot mid-command; the interrupt handler
+            // forgets it
+            Err(_) if self.registers.read(SDHCI_PRESENT_STATE) & PRESENT_CARD_INSERTED == 0 => {
+                return Err(DriverError::DeviceNotFound);
+            }
+            Err(error) => return Err(error),
+        };
+        if status & R1_ERRORS != 0 {
+            return Err(DriverError::IoError);
+        }
+        if read {
+            buffer.read(0, &mut data[..length]);
+        }
+        Ok(())
+    }
+
+    /// Check `count` blocks at `lba` are on the card and fit `length`
+    fn check_range(&self, lba: u64, count: u32, length: usize) -> DriverResult<Card> {
+        let card = self.card.ok_or(DriverError::DeviceNotFound)?;
+        let end = lba.checked_add(count as u64).ok_or(DriverError::InvalidParameter)?;
+        if end > card.blocks || length < (count * SD_BLOCK_SIZE) as usize {
+            return Err(DriverError::InvalidParameter);
+        }
+        // Standard capacity cards take a 32-bit byte address
+        if card.kind == CardKind::Sdsc && end * SD_BLOCK_SIZE as u64 > u32::MAX as u64 {
+            return Err(DriverError::InvalidParameter);
+        }
+        Ok(card)
+    }
+
+    /// Serve a raw disk control request (see orion_blkio::control)
+    pub fn block_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let read_only = self.card.is_some_and(|card| card.read_only);
+        let result = match (self.card, ControlRequest::decode(request, SD_BLOCK_SIZE)) {
+            (_, None) => Err(STATUS_EINVAL),
+            (None, Some(_)) => Err(STATUS_ENODEV),
+            (Some(card), Some(ControlRequest::Info)) => {
+                let flags = if read_only { BLK_INFO_READ_ONLY } else { 0 };
+                Ok(encode_info(&DiskInfo { block_size: SD_BLOCK_SIZE, flags, blocks: card.blocks }))
+            }
+            (_, Some(ControlRequest::Read { lba, count })) => {
+                let mut buffer = vec![0u8; (count * SD_BLOCK_SIZE) as usize];
+                self.read_blocks(lba, count, &mut buffer).map(|_| buffer).map_err(status_of)
+            }
+            (_, Some(ControlRequest::Write { .. })) if read_only => Err(STATUS_EROFS),
+            (_, Some(ControlRequest::Write { lba, data })) => {
+                let count = data.len() as u32 / SD_BLOCK_SIZE;
+                self.write_blocks(lba, count, data).map(|_| Vec::new()).map_err(status_of)
+            }
+            // A write is on the card once its busy phase ended
+            (_, Some(ControlRequest::Flush)) => Ok(Vec::new()),
+            (_, Some(ControlRequest::ReadIntegrity { lba, count, algorithm })) => {
+                let mut buffer = vec![0u8; (count * SD_BLOCK_SIZE) as usize];
+                self.read_blocks(lba, count, &mut buffer)
+                    .map(|_| encode_integrity_read(algorithm, SD_BLOCK_SIZE, &buffer))
+                    .map_err(status_of)
+            }
+            (_, Some(ControlRequest::WriteIntegrity { .. })) if read_only => Err(STATUS_EROFS),
+            (_, Some(ControlRequest::WriteIntegrity { lba, checksums, data })) => {
+                if checksums.verify(data).is_err() {
+                    Err(STATUS_EBADMSG)
+                } else {
+                    let count = data.len() as u32 / SD_BLOCK_SIZE;
+                    self.write_blocks(lba, count, data).map(|_| Vec::new()).map_err(status_of)
+                }
+            }
+        };
+        match result {
+            Ok(payload) => reply(STATUS_OK, &payload),
+            Err(status) => reply(status, &[]),
+        }
+    }
+}
+
+/// The ADMA2 table moving `length` bytes at `address`, in descriptors of
+/// at most ADMA2_MAX_LENGTH; 32-bit descriptors keep the low half of the
+/// address
+fn adma2_table(address: u64, length: usize, adma64: bool) -> Vec<u8> {
+    let descriptor_size = if adma64 { 12 } else { 8 };
+    let descriptors = length.div_ceil(ADMA2_MAX_LENGTH);
+    let mut table = Vec::with_capacity(descriptors * descriptor_size);
+    for index in 0..descriptors {
+        let offset = index * ADMA2_MAX_LENGTH;
+        let chunk = ADMA2_MAX_LENGTH.min(length - offset);
+        let end = if index + 1 == descriptors { ADMA2_END } else { 0 };
+        table.extend_from_slice(&(ADMA2_VALID | ADMA2_TRAN | end).to_le_bytes());
+        table.extend_from_slice(&(chunk as u16).to_le_bytes());
+        table.extend_from_slice(&(address + offset as u64).to_le_bytes()[..descriptor_size - 4]);
+    }
+    table
+}
+
+/// Reply status of a failed transfer
+fn status_of(error: DriverError) -> i32 {
+    match error {
+        DriverError::DeviceNotFound => STATUS_ENODEV,
+        DriverError::Timeout => STATUS_ETIMEDOUT,
+        DriverError::InvalidParameter => STATUS_EINVAL,
+        _ => STATUS_EIO,
+    }
+}
+
+impl<R: SdhciRegisters, A: DmaAllocator> BlockDriver for Sdhci<R, A> {
+    fn read_blocks(&mut self, lba: u64, count: u32, buffer: &mut [u8]) -> DriverResult<usize> {
+        self.check_range(lba, count, buffer.len())?;
+        let mut done = 0;
+        while done < count {
+            let run = SDHCI_MAX_BLOCKS.min(count - done);
+            let offset = (done * SD_BLOCK_SIZE) as usize;
+            self.transfer(lba + done as u64, run, &mut buffer[offset..], true)?;
+            done += run;
+        }
+        Ok((count * SD_BLOCK_SIZE) as usize)
+    }
+
+    fn write_blocks(&mut self, lba: u64, count: u32, buffer: &[u8]) -> DriverResult<usize> {
+        if self.check_range(lba, count, buffer.len())?.read_only {
+            return Err(DriverError::InvalidParameter);
+        }
+        let mut done = 0;
+        while done < count {
+            let run = SDHCI_MAX_BLOCKS.min(count - done);
+            let offset = (done * SD_BLOCK_SIZE) as usize;
+            let mut chunk = buffer[offset..offset + (run * SD_BLOCK_SIZE) as usize].to_vec();
+            self.transfer(lba + done as u64, run, &mut chunk, false)?;
+            done += run;
+        }
+        Ok((count * SD_BLOCK_SIZE) as usize)
+    }
+
+    fn get_capacity(&self) -> DriverResult<u64> {
+        self.card.map(|card| card.blocks).ok_or(DriverError::DeviceNotFound)
+    }
+
+    fn get_block_size(&self) -> DriverResult<u32> {
+        Ok(SD_BLOCK_SIZE)
+    }
+}
+
+// ========================================
+// DEVICE TREE
+// ========================================
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Split an SDHCI_IOCTL_FDT_NODE request into `compatible`, `reg` and the
+/// parent's address and size cells
+pub fn decode_fdt_node(request: &[u8]) -> Option<(&[u8], &[u8], u32, u32)> {
+    let compatible_length = read_u32(request, 0)? as usize;
+    let compatible = request.get(4..4 + compatible_length)?;
+    let offset = 4 + compatible_length;
+    let reg_length = read_u32(request, offset)? as usize;
+    let reg = request.get(offset + 4..offset + 4 + reg_length)?;
+    let offset = offset + 4 + reg_length;
+    Some((compatible, reg, read_u32(request, offset)?, read_u32(request, offset + 4)?))
+}
+
+/// The base of the registers of a device tree node, when it is a
+/// controller the driver handles: `compatible` is NUL separated strings
+/// and the first entry of `reg` big-endian cells of the parent's sizes
+pub fn fdt_controller(compatible: &[u8], reg: &[u8], address_cells: u32, size_cells: u32) -> Option<u64> {
+    let listed = compatible
+        .split(|&byte| byte == 0)
+        .any(|entry| core::str::from_utf8(entry).is_ok_and(|entry| SDHCI_FDT_COMPATIBLE.contains(&entry)));
+    if !listed || !(1..=2).contains(&address_cells) || !(1..=2).contains(&size_cells) {
+        return None;
+    }
+    let cells = |first: u32, count: u32| -> Option<u64> {
+        (first..first + count).try_fold(0u64, |value, cell| {
+            let bytes = reg.get(4 * cell as usize..4 * cell as usize + 4)?;
+            Some(value << 32 | u32::from_be_bytes(bytes.try_into().ok()?) as u64)
+        })
+    };
+    let base = cells(0, address_cells)?;
+    let size = cells(address_cells, size_cells)?;
+    (base != 0 && size >= SDHCI_WINDOW_SIZE as u64).then_some(base)
+}
+
+// ========================================
+// ORION DRIVER IMPLEMENTATION
+// ========================================
+
+pub type SdhciDriver = Sdhci<MmioAccessor, DmaRegion>;
+
+impl SdhciDriver {
+    /// Take the controller whose registers are at `base`
+    fn map(base: u64) -> DriverResult<Self> {
+        let mmio = unsafe {
+            MmioAccessor::new(
+                base,
+                SDHCI_WINDOW_SIZE,
+                MmioPermissions::READ | MmioPermissions::WRITE | MmioPermissions::UNCACHED,
+            )
+        };
+        let leaks = Rc::new(LeakTracker::new("sdhci"));
+        let dma = DmaPool::new(DmaRegion::new(SDHCI_DMA_BASE, SDHCI_DMA_SIZE), SDHCI_DMA_ALIGN, leaks);
+        Sdhci::new(mmio, dma)
+    }
+}
+
+impl OrionDriver for SdhciDriver {
+    fn probe(device: &DeviceInfo) -> DriverResult<bool> {
+        Ok(SDHCI_PCI_IDS.contains(&(device.vendor_id, device.device_id))
+            || (device.class == PCI_CLASS_SYSTEM && device.subclass == PCI_SUBCLASS_SDHCI))
+    }
+
+    fn init(device: DeviceInfo) -> DriverResult<Self> {
+        Self::map(device.bars[0])
+    }
+
+    fn handle_irq(&mut self) -> DriverResult<()> {
+        self.interrupt();
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> DriverResult<()> {
+        self.card = None;
+        self.registers.write(SDHCI_SIGNAL_ENABLE, 0);
+        self.reset(RESET_ALL)
+    }
+
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "SD Host Controller Driver",
+            version: "1.0.0",
+            author: "Jeremy Noverraz",
+            description: "SDHCI driver for SDSC, SDHC and SDXC cards with ADMA2 and card detect",
+        }
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut message_loop = match MessageLoop::new() {
+        Ok(loop_obj) => loop_obj,
+        Err(_) => return,
+    };
+
+    static mut DRIVER: Option<SdhciDriver> = None;
+
+    let vendors: Vec<u16> = SDHCI_PCI_IDS.iter().map(|&(vendor, _)| vendor).collect();
+    let devices: Vec<u16> = SDHCI_PCI_IDS.iter().map(|&(_, device)| device).collect();
+    let _ = message_loop.run("sdhci", "1.0.0", &vendors, &devices, |ipc, message| match message {
+        ReceivedMessage::ProbeDevice(probe_msg) => {
+            let device = DeviceInfo::new(probe_msg.vendor_id, probe_msg.device_id, orion_driver::BusType::Pci);
+            let can_handle = SdhciDriver::probe(&device).unwrap_or(false);
+            ipc.send_probe_response(probe_msg.header.sequence, can_handle)
+        }
+
+        ReceivedMessage::InitDevice(device_handle) => {
+            let (vendor, device) = SDHCI_PCI_IDS[0];
+            let mut device = DeviceInfo::new(vendor, device, orion_driver::BusType::Pci);
+            device.bars[0] = device_handle;
+            match SdhciDriver::init(device) {
+                Ok(driver) => {
+                    unsafe { DRIVER = Some(driver) };
+                    ipc.send_io_response(0, Ok(0))
+                }
+                Err(e) => ipc.send_io_response(0, Err(e)),
+            }
+        }
+
+        ReceivedMessage::IoRequest(io_msg) => {
+            let driver = unsafe { (*core::ptr::addr_of_mut!(DRIVER)).as_mut() };
+            match (driver, &io_msg.request_type) {
+                // A device tree board: the node is the controller's only description
+                (None, orion_driver::IoRequestType::Ioctl) if io_msg.length == SDHCI_IOCTL_FDT_NODE => {
+                    let base = decode_fdt_node(&io_msg.data)
+                        .and_then(|(compatible, reg, address_cells, size_cells)| {
+                            fdt_controller(compatible, reg, address_cells, size_cells)
+                        })
+                        .ok_or(DriverError::DeviceNotFound);
+                    match base.and_then(SdhciDriver::map) {
+                        Ok(driver) => {
+                            unsafe { DRIVER = Some(driver) };
+                            ipc.send_io_response(io_msg.header.sequence, Ok(0))
+                        }
+                        Err(e) => ipc.send_io_response(io_msg.header.sequence, Err(e)),
+                    }
+                }
+                (Some(driver), orion_driver::IoRequestType::Ioctl) if io_msg.length == BLK_IOCTL_CONTROL => {
+                    // Raw disk requests answer with data rather than a length
+                    let reply = driver.block_control(&io_msg.data);
+                    ipc.send_response(io_msg.header.sequence, 0, &reply)
+                }
+                (Some(_), _) => ipc.send_io_response(io_msg.header.sequence, Err(DriverError::Unsupported)),
+                (None, _) => ipc.send_io_response(io_msg.header.sequence, Err(DriverError::DeviceNotReady)),
+            }
+        }
+
+        ReceivedMessage::Interrupt(_device_handle) => {
+            let result = match unsafe { (*core::ptr::addr_of_mut!(DRIVER)).as_mut() } {
+                Some(driver) => driver.handle_irq().map(|_| 0),
+                None => Err(DriverError::DeviceNotReady),
+            };
+            ipc.send_io_response(0, result)
+        }
+
+        ReceivedMessage::Shutdown => {
+            if let Some(driver) = unsafe { (*core::ptr::addr_of_mut!(DRIVER)).as_mut() } {
+                let _ = driver.shutdown();
+            }
+            Ok(())
+        }
+
+        ReceivedMessage::Unknown => Ok(()),
+    });
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::alloc::{alloc, Layout};
+
+    const HIGH_CAPACITY_BYTES: usize = 2 * 1024 * 1024;
+    const STANDARD_CAPACITY_BYTES: usize = 1024 * 1024;
+
+    /// The card in the simulated slot
+    struct FakeCard {
+        memory: Vec<u8>,
+        high_capacity: bool,
+        app: bool,
+        rca: u16,
+    }
+
+    impl FakeCard {
+        fn new(bytes: usize, high_capacity: bool) -> Self {
+            Self { memory: vec![0; bytes], high_capacity, app: false, rca: 0 }
+        }
+
+        fn csd(&self) -> u128 {
+            let blocks = (self.memory.len() / SD_BLOCK_SIZE as usize) as u128;
+            if self.high_capacity {
+                1 << 126 | (blocks / 1024 - 1) << 48
+            } else {
+                // 512 byte blocks, a multiplier of 512
+                9 << 80 | (blocks / 512 - 1) << 62 | 7 << 47
+            }
+        }
+    }
+
+    /// A controller running commands at once against the card in its
+    /// slot, reading and writing memory through the ADMA2 table
+    struct FakeSdhci {
+        registers: [u32; 64],
+        card: Option<FakeCard>,
+        /// Descriptors of the last data command as (attributes, length, address)
+        chain: Vec<(u16, usize, u64)>,
+        /// Data commands to run before the card is pulled out mid-command
+        eject_after: Option<usize>,
+        ejected: Option<FakeCard>,
+    }
+
+    impl FakeSdhci {
+        fn new(card: Option<FakeCard>) -> Self {
+            Self { registers: [0; 64], card, chain: Vec::new(), eject_after: None, ejected: None }
+        }
+
+        fn set(&mut self, register: u64, value: u32) {
+            self.registers[register as usize / 4] = value;
+        }
+
+        fn get(&self, register: u64) -> u32 {
+            self.registers[register as usize / 4]
+        }
+
+        fn execute(&mut self, command: u32) {
+            let index = (command >> 24) & 0x3F;
+            let argument = self.get(SDHCI_ARGUMENT);
+            if (command >> 16) & COMMAND_DATA != 0 {
+                match self.eject_after {
+                    Some(0) => {
+                        self.eject_after = None;
+                        self.ejected = self.swap(None);
+                    }
+                    Some(commands) => self.eject_after = Some(commands - 1),
+                    None => {}
+                }
+            }
+            let adma64 = self.get(SDHCI_HOST_CONTROL) & HOST_DMA_MASK == HOST_ADMA2_64;
+            let Some(card) = self.card.as_mut() else {
+                self.set(SDHCI_INT_STATUS, self.get(SDHCI_INT_STATUS) | INT_ERROR | INT_CMD_TIMEOUT);
+                return;
+            };
+            let app = core::mem::take(&mut card.app);
+            let mut response = [0u32; 4];
+            match (index, app) {
+                (CMD_SEND_IF_COND, _) if !card.high_capacity => {
+                    self.set(SDHCI_INT_STATUS, self.get(SDHCI_INT_STATUS) | INT_ERROR | INT_CMD_TIMEOUT);
+                    return;
+                }
+                (CMD_SEND_IF_COND, _) => response[0] = argument & 0xFFF,
+                (CMD_APP_CMD, _) => card.app = true,
+                (ACMD_SD_SEND_OP_COND, true) => {
+                    let ccs = if card.high_capacity && argument & OCR_HCS != 0 { OCR_CCS } else { 0 };
+                    response[0] = OCR_READY | ccs | 0x00FF_8000;
+                }
+                (CMD_SEND_RELATIVE_ADDR, _) => {
+                    card.rca = 0x1234;
+                    response[0] = (card.rca as u32) << 16;
+                }
+                (CMD_SEND_CSD, _) => {
+                    let csd = card.csd() >> 8;
+                    for (word, value) in response.iter_mut().enumerate() {
+                        *value = (csd >> (32 * word)) as u32;
+                    }
+                }
+                (CMD_READ_SINGLE | CMD_READ_MULTIPLE | CMD_WRITE_SINGLE | CMD_WRITE_MULTIPLE, _) => {
+                    let offset = if card.high_capacity { argument as usize * 512 } else { argument as usize };
+                    let read = command & MODE_READ != 0;
+                    let mut table = self.registers[SDHCI_ADMA_ADDRESS as usize / 4] as u64
+                        | (self.registers[SDHCI_ADMA_ADDRESS_HIGH as usize / 4] as u64) << 32;
+                    let descriptor_size = if adma64 { 12 } else { 8 };
+                    let mut position = offset;
+                    self.chain.clear();
+                    loop {
+                        let descriptor = unsafe { core::slice::from_raw_parts(table as *const u8, descriptor_size) };
+                        let attributes = u16::from_le_bytes([descriptor[0], descriptor[1]]);
+                        let length = u16::from_le_bytes([descriptor[2], descriptor[3]]) as usize;
+                        let mut address = [0u8; 8];
+                        address[..descriptor_size - 4].copy_from_slice(&descriptor[4..]);
+                        let address = u64::from_le_bytes(address);
+                        self.chain.push((attributes, length, address));
+                        let data = unsafe { core::slice::from_raw_parts_mut(address as *mut u8, length) };
+                        if read {
+                            data.copy_from_slice(&card.memory[position..position + length]);
+                        } else {
+                            card.memory[position..position + length].copy_from_slice(data);
+                        }
+                        position += length;
+                        table += descriptor_size as u64;
+                        if attributes & ADMA2_END != 0 {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            for (word, value) in response.iter().enumerate() {
+                self.set(SDHCI_RESPONSE + 4 * word as u64, *value);
+            }
+            let mut status = INT_CMD_COMPLETE;
+            if (command >> 16) & COMMAND_DATA != 0 || (command >> 16) & 3 == RESPONSE_48_BUSY {
+                status |= INT_TRANSFER_COMPLETE;
+            }
+            self.set(SDHCI_INT_STATUS, self.get(SDHCI_INT_STATUS) | status);
+        }
+
+        /// Take the card out of the slot or put one in
+        fn swap(&mut self, card: Option<FakeCard>) -> Option<FakeCard> {
+            let event = if card.is_some() { INT_CARD_INSERTION } else { INT_CARD_REMOVAL };
+            self.set(SDHCI_INT_STATUS, self.get(SDHCI_INT_STATUS) | event);
+            core::mem::replace(&mut self.card, card)
+        }
+    }
+
+    impl SdhciRegisters for FakeSdhci {
+        fn read(&mut self, register: u64) -> u32 {
+            match register {
+                SDHCI_PRESENT_STATE if self.card.is_some() => PRESENT_CARD_INSERTED | PRESENT_WRITE_ENABLED,
+                SDHCI_PRESENT_STATE => 0,
+                SDHCI_CAPABILITIES => 50 << CAP_BASE_CLOCK_SHIFT | CAP_ADMA2 | CAP_VOLTAGE_3V3 | CAP_64BIT,
+                SDHCI_HOST_VERSION => SPEC_VERSION_3 << 16,
+                _ => self.get(register),
+            }
+        }
+
+        fn write(&mut self, register: u64, value: u32) {
+            match register {
+                SDHCI_INT_STATUS => self.set(register, self.get(register) & !value),
+                SDHCI_COMMAND => {
+                    self.set(register, value);
+                    self.execute(value);
+                }
+                SDHCI_CLOCK_CONTROL => {
+                    if value & RESET_ALL != 0 {
+                        self.registers = [0; 64];
+                    }
+                    let stable = if value & CLOCK_INTERNAL_ENABLE != 0 { CLOCK_INTERNAL_STABLE } else { 0 };
+                    self.set(register, (value & 0x00FF_FFFF & !CLOCK_INTERNAL_STABLE) | stable);
+                }
+                _ => self.set(register, value),
+            }
+        }
+    }
+
+    fn controller(card: Option<FakeCard>) -> Sdhci<FakeSdhci, DmaRegion> {
+        let layout = Layout::from_size_align(0x20000, 0x1000).unwrap();
+        let arena = unsafe { alloc(layout) };
+        let dma =
+            DmaPool::new(DmaRegion::new(arena as u64, 0x20000), SDHCI_DMA_ALIGN, Rc::new(LeakTracker::new("test")));
+        Sdhci::new(FakeSdhci::new(card), dma).unwrap()
+    }
+
+    fn request(opcode: u32, lba: u64, tail: &[u8]) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        request.extend_from_slice(&lba.to_le_bytes());
+        request.extend_from_slice(tail);
+        request
+    }
+
+    #[test]
+    fn test_brings_up_cards_and_divides_the_clock() {
+        let sdhci = controller(Some(FakeCard::new(HIGH_CAPACITY_BYTES, true)));
+        let card = sdhci.card().unwrap();
+        assert_eq!((card.kind, card.rca, card.blocks, card.read_only), (CardKind::Sdhc, 0x1234, 4096, false));
+        // 50 MHz to 400 kHz divides by 2 * 63, to 25 MHz by 2
+        assert_eq!(sdhci.clock_divider(CLOCK_IDENTIFICATION_KHZ), 63 << 8);
+        assert_eq!(sdhci.clock_divider(CLOCK_DEFAULT_SPEED_KHZ), 1 << 8);
+        assert_eq!(
+            sdhci.registers.get(SDHCI_HOST_CONTROL) & (HOST_DMA_MASK | HOST_4BIT_BUS),
+            HOST_ADMA2_64 | HOST_4BIT_BUS
+        );
+
+        let sdsc = controller(Some(FakeCard::new(STANDARD_CAPACITY_BYTES, false)));
+        assert_eq!(sdsc.card().map(|card| (card.kind, card.blocks)), Some((CardKind::Sdsc, 2048)));
+        assert_eq!(parse_csd(1 << 126 | 0x1_0000 << 48), Some((CardKind::Sdxc, 0x1_0001 * 1024)));
+    }
+
+    #[test]
+    fn test_moves_blocks_through_adma2() {
+        for (bytes, high_capacity) in [(HIGH_CAPACITY_BYTES, true), (STANDARD_CAPACITY_BYTES, false)] {
+            let mut sdhci = controller(Some(FakeCard::new(bytes, high_capacity)));
+            // 100 blocks take two descriptors
+            let data: Vec<u8> = (0..100 * 512).map(|index| (index % 251) as u8).collect();
+            assert_eq!(sdhci.block_control(&request(0x2003, 7, &data)), reply(STATUS_OK, &[]));
+            assert_eq!(sdhci.registers.card.as_ref().unwrap().memory[7 * 512..107 * 512], data[..]);
+            let chain = &sdhci.registers.chain;
+            let (first, last) = (ADMA2_VALID | ADMA2_TRAN, ADMA2_VALID | ADMA2_TRAN | ADMA2_END);
+            assert_eq!(chain[..], [(first, 0x8000, chain[0].2), (last, 18432, chain[0].2 + 0x8000)]);
+
+            let read = sdhci.block_control(&request(0x2002, 8, &3u32.to_le_bytes()));
+            assert_eq!(read, reply(STATUS_OK, &data[512..4 * 512]));
+            let past_end = sdhci.block_control(&request(0x2002, (bytes / 512) as u64, &1u32.to_le_bytes()));
+            assert_eq!(past_end, reply(STATUS_EINVAL, &[]));
+        }
+    }
+
+    #[test]
+    fn test_lays_out_adma2_tables_in_both_formats() {
+        let descriptor = |end: bool, length: u16, address: &[u8]| {
+            let attributes = ADMA2_VALID | ADMA2_TRAN | if end { ADMA2_END } else { 0 };
+            [&attributes.to_le_bytes()[..], &length.to_le_bytes(), address].concat()
+        };
+        // A full command of 128 blocks takes two full descriptors
+        let wide = adma2_table(0x1_2345_0000, 128 * 512, true);
+        let expected = [
+            descriptor(false, 0x8000, &0x1_2345_0000u64.to_le_bytes()),
+            descriptor(true, 0x8000, &0x1_2345_8000u64.to_le_bytes()),
+        ];
+        assert_eq!(wide, expected.concat());
+
+        let narrow = adma2_table(0x0010_0000, 100 * 512, false);
+        let expected = [
+            descriptor(false, 0x8000, &0x0010_0000u32.to_le_bytes()),
+            descriptor(true, 18432, &0x0010_8000u32.to_le_bytes()),
+        ];
+        assert_eq!(narrow, expected.concat());
+        assert_eq!(adma2_table(0, 512, false), descriptor(true, 512, &[0; 4]));
+    }
+
+    #[test]
+    fn test_answers_enodev_when_the_card_leaves_mid_request() {
+        let mut sdhci = controller(Some(FakeCard::new(HIGH_CAPACITY_BYTES, true)));
+        // 200 blocks go out as 128 then 72; the card is pulled during the second
+        let data: Vec<u8> = (0..200 * 512).map(|index| (index % 253) as u8).collect();
+        sdhci.registers.eject_after = Some(1);
+        assert_eq!(sdhci.write_blocks(0, 200, &data), Err(DriverError::DeviceNotFound));
+        sdhci.interrupt();
+        assert_eq!(sdhci.card(), None);
+        assert_eq!(sdhci.block_control(&0x2001u32.to_le_bytes()), reply(STATUS_ENODEV, &[]));
+
+        // Back in the slot, the card holds the first command's blocks only
+        let card = sdhci.registers.ejected.take();
+        sdhci.registers.swap(card);
+        sdhci.interrupt();
+        let mut read = vec![0xFF; 200 * 512];
+        assert_eq!(sdhci.read_blocks(0, 200, &mut read), Ok(200 * 512));
+        assert_eq!(read[..128 * 512], data[..128 * 512]);
+        assert!(read[128 * 512..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_takes_device_tree_controllers() {
+        let compatible = b"brcm,bcm2711-emmc2\0brcm,sdhci\0";
+        let reg = [0u32, 0xFE34_0000, 0, 0x100].map(u32::to_be_bytes).concat();
+        let mut node = Vec::new();
+        for property in [&compatible[..], &reg] {
+            node.extend_from_slice(&(property.len() as u32).to_le_bytes());
+            node.extend_from_slice(property);
+        }
+        node.extend_from_slice(&2u32.to_le_bytes());
+        node.extend_from_slice(&2u32.to_le_bytes());
+        let (compatible, reg, address_cells, size_cells) = decode_fdt_node(&node).unwrap();
+        assert_eq!(fdt_controller(compatible, reg, address_cells, size_cells), Some(0xFE34_0000));
+        assert_eq!(decode_fdt_node(&node[..node.len() - 1]), None);
+
+        let single = [0xFF16_0000u32, 0x1000].map(u32::to_be_bytes).concat();
+        assert_eq!(fdt_controller(b"arasan,sdhci-8.9a\0", &single, 1, 1), Some(0xFF16_0000));
+        // Unknown compatibles, windows too small for the registers and short reg
+        assert_eq!(fdt_controller(b"vendor,mmc\0", &single, 1, 1), None);
+        let small = [0xFF16_0000u32, 0x80].map(u32::to_be_bytes).concat();
+        assert_eq!(fdt_controller(b"arasan,sdhci-8.9a\0", &small, 1, 1), None);
+        assert_eq!(fdt_controller(b"arasan,sdhci-8.9a\0", &single, 2, 2), None);
+    }
+
+    #[test]
+    fn test_follows_the_card_in_and_out_of_the_slot() {
+        let mut sdhci = controller(Some(FakeCard::new(HIGH_CAPACITY_BYTES, true)));
+        let info = 0x2001u32.to_le_bytes();
+        sdhci.registers.swap(None);
+        sdhci.interrupt();
+        assert_eq!(sdhci.card(), None);
+        assert_eq!(sdhci.block_control(&info), reply(STATUS_ENODEV, &[]));
+        assert_eq!(sdhci.get_capacity(), Err(DriverError::DeviceNotFound));
+
+        sdhci.registers.swap(Some(FakeCard::new(STANDARD_CAPACITY_BYTES, false)));
+        sdhci.interrupt();
+        let expected = DiskInfo { block_size: SD_BLOCK_SIZE, flags: 0, blocks: 2048 };
+        assert_eq!(sdhci.block_control(&info), reply(STATUS_OK, &encode_info(&expected)));
+    }
+}
@@ -0,0 +1,529 @@
+/*
+ * Orion Operating System - Verity Block Driver
+ *
+ * Serves the system image of the running slot as a read-only disk, with
+ * every block checked against the hash tree of the image before it is
+ * handed out (see lib/orion_verity). Nothing mounts the volume yet: the
+ * file system server cannot read a disk file system, and root= is not
+ * consumed at boot, so only clients reading the disk are protected. The root hash
+ * comes from the boot configuration at the head of the image, whose
+ * signature is checked once, at start, against the trust anchors of the
+ * I/O server; a slot whose configuration does not verify is not served
+ * at all.
+ *
+ * A block that does not match fails its read with an I/O error, every
+ * time, and is written to the audit log the first time: the base system
+ * was changed behind the update server's back, or the disk is failing.
+ * The volume cannot be written.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+#![feature(async_fn_in_trait)]
+
+extern crate alloc;
+
+use alloc::{
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use orion_sys::{audit_emit, clock_get, nanosleep};
+use orion_driver::{
+    OrionDriver, BlockDriver, DeviceInfo, DriverError, DriverResult,
+    MessageLoop, PowerState, DeviceState, HotplugEvent,
+};
+use orion_block::{BlockDevice, BlockStats};
+use orion_blockstats::{BlockStatistics, BlockStatsSource, Operation};
+use orion_blkio::{DiskClient, DiskInfo, Transport};
+use orion_crypto::ed25519::PUBLIC_KEY_SIZE;
+use orion_install::{Disk, SystemDisk};
+use orion_ipc::IpcChannel;
+use orion_verity::{config, BlockSource, BootConfig, Verifier, VerityError, VerityStats, BLOCK_SIZE};
+
+/// Audit event types emitted by the driver (user range, see capabilities.c)
+const AUDIT_STORAGE_TAMPER: u32 = 0x1305;
+
+const IO_OP_LIST_DEVICES: u32 = 8;
+const IO_OP_TRUST_ANCHORS: u32 = 9;
+const STATUS_OK: i32 = 0;
+const STATUS_EIO: i32 = -5;
+
+/// Channel of the I/O server or of the driver owning the system disk
+struct DriverIpc(IpcChannel);
+
+impl Transport for DriverIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Whole disk through the raw disk requests of its driver
+struct BlockDisk {
+    client: DiskClient<DriverIpc>,
+    info: DiskInfo,
+}
+
+impl Disk for BlockDisk {
+    fn block_size(&self) -> u32 {
+        self.info.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.info.blocks
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        self.client.read(lba, buffer)
+    }
+
+    fn write(&mut self, _lba: u64, _data: &[u8]) -> Result<(), i32> {
+        Err(STATUS_EIO)
+    }
+
+    fn flush(&mut self) -> Result<(), i32> {
+        Ok(())
+    }
+}
+
+/// System partition of the running slot, in verity blocks
+struct SlotVolume {
+    disk: BlockDisk,
+    first_lba: u64,
+    lbas: u64,
+}
+
+impl BlockSource for SlotVolume {
+    fn read_block(&mut self, index: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        let per_block = (BLOCK_SIZE as u32 / self.disk.info.block_size) as u64;
+        if (index + 1) * per_block > self.lbas {
+            return Err(STATUS_EIO);
+        }
+        self.disk.read(self.first_lba + index * per_block, buffer)
+    }
+}
+
+/// The running slot of the first disk holding a boot control record
+fn find_system_slot() -> Option<SlotVolume> {
+    let response = IpcChannel::connect("io").call(&IO_OP_LIST_DEVICES.to_le_bytes()).ok()?;
+    if response.len() < 4 || response[..4] != STATUS_OK.to_le_bytes() {
+        return None;
+    }
+    // handle, vendor, device, driver pid, then the driver name
+    let mut rest = &response[4..];
+    while rest.len() >= 24 {
+        let length = u32::from_le_bytes(rest[20..24].try_into().ok()?) as usize;
+        let name = core::str::from_utf8(rest.get(24..24 + length)?).ok()?;
+        rest = &rest[24 + length..];
+        if name.is_empty() {
+            continue;
+        }
+        let mut client = DiskClient::new(DriverIpc(IpcChannel::connect(name)));
+        let Ok(info) = client.info() else {
+            continue;
+        };
+        if info.block_size == 0 || BLOCK_SIZE as u32 % info.block_size != 0 {
+            continue;
+        }
+        let mut disk = BlockDisk { client, info };
+        let Ok(system) = SystemDisk::open(&mut disk) else {
+            continue;
+        };
+        let slot = system.slot(system.booted).ok()?;
+        let (first_lba, lbas) = (slot.first_lba, slot.blocks());
+        return Some(SlotVolume { disk, first_lba, lbas });
+    }
+    None
+}
+
+/// Keys the boot configuration may be signed with
+fn trust_anchors() -> Vec<[u8; PUBLIC_KEY_SIZE]> {
+    let Ok(reply) = IpcChannel::connect("io").call(&IO_OP_TRUST_ANCHORS.to_le_bytes()) else {
+        return Vec::new();
+    };
+    if reply.len() < 8 || reply[..4] != STATUS_OK.to_le_bytes() {
+        return Vec::new();
+    }
+    let count = u32::from_le_bytes(reply[4..8].try_into().unwrap()) as usize;
+    let keys = reply.get(8..8 + count * PUBLIC_KEY_SIZE).unwrap_or(&[]);
+    keys.chunks(PUBLIC_KEY_SIZE).map(|key| key.try_into().unwrap()).collect()
+}
+
+/// Hash tree verified read-only system volume
+pub struct VerityDriver<S: BlockSource> {
+    /// Device information
+    info: DeviceInfo,
+    /// Driver state
+    state: DeviceState,
+    /// Power state
+    power_state: PowerState,
+    /// Verified boot configuration of the image
+    config: BootConfig,
+    /// Data blocks checked against the tree
+    verifier: Verifier<S>,
+    /// Blocks found corrupt and already audited
+    reported: BTreeSet<u64>,
+    /// Latency histograms and throughput per operation type
+    io: BlockStatistics,
+    /// Message loop
+    message_loop: MessageLoop,
+}
+
+impl<S: BlockSource> VerityDriver<S> {
+    /// Check the boot configuration at the head of `source` against
+    /// `keys`; Rejected unless it carries a valid signature by one of them
+    pub fn open(mut source: S, keys: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<Self, VerityError> {
+        let mut block = vec![0u8; BLOCK_SIZE];
+        source.read_block(config::CONFIG_BLOCK, &mut block).map_err(VerityError::Io)?;
+        let config = config::verify(&block, keys)?;
+        Ok(Self {
+            info: DeviceInfo {
+                name: "verity".to_string(),
+                version: "1.0.0".to_string(),
+                description: "Verity Block Driver".to_string(),
+                vendor: "ORION OS".to_string(),
+                device_type: "block".to_string(),
+                capabilities: vec!["read_only".to_string(), "integrity".to_string(), "signed".to_string()],
+            },
+            state: DeviceState::Initializing,
+            power_state: PowerState::Active,
+            config,
+            verifier: Verifier::new(source, &config),
+            reported: BTreeSet::new(),
+            io: BlockStatistics::new(),
+            message_loop: MessageLoop::new(),
+        })
+    }
+
+    /// Initialize the verity driver
+    pub async fn initialize(&mut self) -> DriverResult<()> {
+        self.state = DeviceState::Initializing;
+        self.message_loop.start().await?;
+        self.state = DeviceState::Ready;
+        Ok(())
+    }
+
+    /// Boot configuration the volume was opened with
+    pub fn boot_config(&self) -> &BootConfig {
+        &self.config
+    }
+
+    /// Blocks verified, hash blocks read and cached, corrupt blocks
+    pub fn verity_stats(&self) -> VerityStats {
+        self.verifier.stats()
+    }
+
+    fn size(&self) -> u64 {
+        self.verifier.data_blocks() * BLOCK_SIZE as u64
+    }
+
+    /// Read `buffer.len()` bytes from `offset`, a block at a time
+    fn read_verified(&mut self, offset: u64, buffer: &mut [u8]) -> DriverResult<()> {
+        if offset.checked_add(buffer.len() as u64).is_none_or(|end| end > self.size()) {
+            return Err(DriverError::InvalidParameter);
+        }
+        let mut block = vec![0u8; BLOCK_SIZE];
+        let mut done = 0;
+        while done < buffer.len() {
+            let position = offset + done as u64;
+            let (index, within) = (position / BLOCK_SIZE as u64, (position % BLOCK_SIZE as u64) as usize);
+            match self.verifier.read(index, &mut block) {
+                Ok(()) => {}
+                Err(VerityError::Corrupt(volume_block)) => {
+                    self.report(volume_block);
+                    return Err(DriverError::IoError);
+                }
+                Err(VerityError::OutOfRange) => return Err(DriverError::InvalidParameter),
+                Err(_) => return Err(DriverError::IoError),
+            }
+            let count = (BLOCK_SIZE - within).min(buffer.len() - done);
+            buffer[done..done + count].copy_from_slice(&block[within..within + count]);
+            done += count;
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, volume_block: u64) {
+        if !self.reported.insert(volume_block) {
+            return;
+        }
+        let record = format!(
+            "verity-corrupt block={} area={} version={} corrupt={}",
+            volume_block,
+            if volume_block < self.config.hash_start() { "data" } else { "hash" },
+            self.config.version_str(),
+            self.reported.len()
+        );
+        let _ = audit_emit(AUDIT_STORAGE_TAMPER, record.as_bytes());
+    }
+
+    async fn handle_io_message(&mut self, message: orion_driver::IoMessage) -> DriverResult<orion_driver::IoResponse> {
+        match message {
+            orion_driver::IoMessage::Read { offset, length } => {
+                let data = self.read(offset, length).await?;
+                Ok(orion_driver::IoResponse::Read { data })
+            }
+            orion_driver::IoMessage::Write { offset, data } => {
+                let bytes_written = self.write(offset, &data).await?;
+                Ok(orion_driver::IoResponse::Write { bytes_written })
+            }
+            orion_driver::IoMessage::Trim { offset, length } => {
+                let bytes_trimmed = self.trim(offset, length).await?;
+                Ok(orion_driver::IoResponse::Trim { bytes_trimmed })
+            }
+            orion_driver::IoMessage::Flush => {
+                self.flush().await?;
+                Ok(orion_driver::IoResponse::Flush)
+            }
+        }
+    }
+}
+
+impl<S: BlockSource> OrionDriver for VerityDriver<S> {
+    async fn initialize(&mut self) -> DriverResult<()> {
+        self.initialize().await
+    }
+
+    async fn shutdown(&mut self) -> DriverResult<()> {
+        self.state = DeviceState::ShuttingDown;
+        self.message_loop.stop().await?;
+        self.state = DeviceState::Shutdown;
+        Ok(())
+    }
+
+    async fn get_info(&self) -> DriverResult<DeviceInfo> {
+        Ok(self.info.clone())
+    }
+
+    async fn get_version(&self) -> DriverResult<String> {
+        Ok(self.info.version.clone())
+    }
+
+    async fn can_handle(&self, device: &str) -> DriverResult<bool> {
+        Ok(device.starts_with("verity"))
+    }
+
+    async fn get_state(&self) -> DriverResult<DeviceState> {
+        Ok(self.state.clone())
+    }
+
+    async fn set_state(&mut self, state: DeviceState) -> DriverResult<()> {
+        self.state = state;
+        Ok(())
+    }
+
+    async fn get_power_state(&self) -> DriverResult<PowerState> {
+        Ok(self.power_state.clone())
+    }
+
+    async fn set_power_state(&mut self, power_state: PowerState) -> DriverResult<()> {
+        self.power_state = power_state;
+        Ok(())
+    }
+
+    async fn handle_hotplug(&mut self, _event: HotplugEvent) -> DriverResult<()> {
+        // The disk underneath is the system disk; its driver handles removal
+        Ok(())
+    }
+
+    async fn run_message_loop(&mut self) -> DriverResult<()> {
+        self.message_loop.run().await
+    }
+}
+
+impl<S: BlockSource> BlockDriver for VerityDriver<S> {
+    async fn read(&mut self, offset: u64, length: u64) -> DriverResult<Vec<u8>> {
+        let start = monotonic_ns();
+        let mut data = vec![0u8; length as usize];
+        match self.read_verified(offset, &mut data) {
+            Ok(()) => {
+                self.io.record(Operation::Read, length, monotonic_ns().saturating_sub(start));
+                Ok(data)
+            }
+            Err(error) => {
+                self.io.record_error(Operation::Read);
+                Err(error)
+            }
+        }
+    }
+
+    async fn write(&mut self, _offset: u64, _data: &[u8]) -> DriverResult<u64> {
+        self.io.record_error(Operation::Write);
+        Err(DriverError::AccessDenied)
+    }
+
+    async fn trim(&mut self, _offset: u64, _length: u64) -> DriverResult<u64> {
+        self.io.record_error(Operation::Trim);
+        Err(DriverError::AccessDenied)
+    }
+
+    async fn flush(&mut self) -> DriverResult<()> {
+        self.io.record(Operation::Flush, 0, 0);
+        Ok(())
+    }
+
+    async fn get_device_info(&mut self) -> DriverResult<BlockDevice> {
+        Ok(BlockDevice {
+            name: "verity0".to_string(),
+            size: self.size(),
+            block_size: BLOCK_SIZE as u32,
+            read_only: true,
+            supports_trim: false,
+            supports_flush: true,
+            supports_write_zeroes: false,
+            supports_block_status: false,
+        })
+    }
+
+    async fn get_block_stats(&mut self) -> DriverResult<BlockStats> {
+        let report = self.io.report();
+        Ok(BlockStats {
+            bytes_read: report.read.bytes,
+            bytes_written: report.write.bytes,
+            total_operations: report.total_operations(),
+            read_operations: report.read.operations,
+            write_operations: report.write.operations,
+            trim_operations: report.trim.operations,
+            flush_operations: report.flush.operations,
+            error_count: report.total_errors(),
+            avg_latency: report.latency.mean,
+            max_latency: report.latency.max,
+            min_latency: report.latency.min,
+        })
+    }
+
+    // A block is only trusted as read; keeping it would skip the check
+    async fn cache_get(&mut self, _offset: u64, _length: u64) -> DriverResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn cache_put(&mut self, _offset: u64, _data: &[u8]) -> DriverResult<()> {
+        Ok(())
+    }
+
+    async fn cache_trim(&mut self, _offset: u64, _length: u64) -> DriverResult<()> {
+        Ok(())
+    }
+
+    async fn cache_flush(&mut self) -> DriverResult<()> {
+        Ok(())
+    }
+}
+
+impl<S: BlockSource> BlockStatsSource for VerityDriver<S> {
+    fn block_statistics(&self) -> &BlockStatistics {
+        &self.io
+    }
+}
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn idle_ns(duration: u64) {
+    let _ = nanosleep(duration);
+}
+
+// Main driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() -> i32 {
+    let Some(volume) = find_system_slot() else {
+        eprintln!("verity: no system slot to serve");
+        return -1;
+    };
+    let mut driver = match VerityDriver::open(volume, &trust_anchors()) {
+        Ok(driver) => driver,
+        Err(error) => {
+            eprintln!("verity: system image refused: {:?}", error);
+            let record = format!("verity-refused error={:?}", error);
+            let _ = audit_emit(AUDIT_STORAGE_TAMPER, record.as_bytes());
+            return -1;
+        }
+    };
+
+    orion_async::set_clock(monotonic_ns);
+    orion_async::set_idle(idle_ns);
+    match orion_async::block_on(driver.initialize()) {
+        Ok(_) => {
+            println!(
+                "verity: system {} ready, {} MiB verified on read",
+                driver.config.version_str(),
+                driver.size() >> 20
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to initialize verity driver: {:?}", e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_crypto::ed25519;
+
+    const SECRET: [u8; 32] = [21; 32];
+
+    struct MemoryVolume(Vec<u8>);
+
+    impl BlockSource for MemoryVolume {
+        fn read_block(&mut self, index: u64, buffer: &mut [u8]) -> Result<(), i32> {
+            let start = index as usize * BLOCK_SIZE;
+            buffer.copy_from_slice(self.0.get(start..start + BLOCK_SIZE).ok_or(STATUS_EIO)?);
+            Ok(())
+        }
+    }
+
+    fn image() -> (Vec<u8>, Vec<u8>) {
+        let data: Vec<u8> = (0..20 * BLOCK_SIZE).map(|byte| (byte % 253) as u8).collect();
+        let image = orion_verity::seal(&data, b"2025.08.1\0\0\0\0\0\0\0", &[1; 32], &SECRET);
+        (data, image)
+    }
+
+    #[tokio::test]
+    async fn test_verity_serves_signed_images() {
+        let (data, image) = image();
+        let keys = [ed25519::public_key(&SECRET)];
+        let mut driver = VerityDriver::open(MemoryVolume(image.clone()), &keys).unwrap();
+        assert!(driver.initialize().await.is_ok());
+        assert_eq!(driver.boot_config().version_str(), "2025.08.1");
+
+        // Reads need not be aligned
+        assert_eq!(driver.read(4000, 5000).await.unwrap(), data[4000..9000]);
+        assert!(matches!(driver.read(19 * 4096, 4097).await, Err(DriverError::InvalidParameter)));
+        assert!(matches!(driver.write(0, &[0; 512]).await, Err(DriverError::AccessDenied)));
+        assert!(driver.get_device_info().await.unwrap().read_only);
+
+        let other = [ed25519::public_key(&[22; 32])];
+        assert!(matches!(VerityDriver::open(MemoryVolume(image), &other), Err(VerityError::Rejected)));
+    }
+
+    #[tokio::test]
+    async fn test_verity_fails_tampered_blocks() {
+        let (data, mut image) = image();
+        image[(1 + 7) * BLOCK_SIZE + 100] ^= 1;
+        let keys = [ed25519::public_key(&SECRET)];
+        let mut driver = VerityDriver::open(MemoryVolume(image), &keys).unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(driver.read(7 * 4096, 4096).await, Err(DriverError::IoError)));
+        }
+        assert_eq!(driver.read(8 * 4096, 4096).await.unwrap(), data[8 * 4096..9 * 4096]);
+        let stats = driver.verity_stats();
+        assert_eq!((stats.corrupt, stats.last_corrupt), (2, Some(8)));
+        assert_eq!(driver.reported.len(), 1);
+        assert_eq!(driver.get_block_stats().await.unwrap().error_count, 2);
+    }
+}
@@ -43,6 +43,15 @@ use orion_driver::{
 };
 use orion_async::{Future, Pin, Poll, Context, Waker, AsyncMutex, AsyncChannel, AsyncRwLock};
 use orion_crypto::{Aes256, ChaCha20Poly1305, Blake3};
+use orion_blkio::control::{
+    encode_info, encode_integrity_read, ControlRequest, DiskInfo, BLK_INFO_READ_ONLY, STATUS_EBADMSG, STATUS_EINVAL,
+    STATUS_EIO, STATUS_EROFS, STATUS_OK,
+};
+use orion_blkio::BLK_IOCTL_CONTROL;
+use orion_probe::points::{BLOCK_READ_BLOCKS, BLOCK_WRITE_BLOCKS, DRIVER_HANDLE_IRQ};
+use orion_probe::{probe, Probes, PROBE_IOCTL_CONTROL};
+use orion_proto::reply;
+use orion_sys::clock_get;
 use alloc::{
     vec::Vec, collections::{BTreeMap, VecDeque}, boxed::Box, 
     string::String, sync::Arc
@@ -88,6 +97,15 @@ static ALLOCATOR: AdvancedAsyncAllocator = AdvancedAsyncAllocator {
     peak_usage: AsyncMutex::new(0),
 };
 
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// Trait method probes, switched by orion-trace
+static PROBES: Probes = Probes::new(monotonic_ns);
+
 // ========================================
 // VIRTIO CONSTANTS AND ENUMS
 // ========================================
@@ -613,6 +631,9 @@ impl VirtioBlockDriver {
         if (device_features & VIRTIO_BLK_F_BLK_SIZE) != 0 {
             driver_features |= VIRTIO_BLK_F_BLK_SIZE;
         }
+        if (device_features & VIRTIO_BLK_F_RO) != 0 {
+            driver_features |= VIRTIO_BLK_F_RO;
+        }
         if (device_features & VIRTIO_BLK_F_FLUSH) != 0 {
             driver_features |= VIRTIO_BLK_F_FLUSH;
         }
@@ -1154,18 +1175,20 @@ impl OrionDriver for VirtioBlockDriver {
     }
 
     async fn handle_irq(&mut self) -> DriverResult<()> {
-        // Read interrupt status
-        let interrupt_status = self.read_mmio(VIRTIO_MMIO_INTERRUPT_STATUS);
-        
-        if interrupt_status != 0 {
-            // Process queue completions
-            self.process_queue_completions().await?;
-            
-            // Acknowledge interrupt
-            self.write_mmio(VIRTIO_MMIO_INTERRUPT_ACK, interrupt_status);
-        }
-        
-        Ok(())
+        probe!(PROBES, DRIVER_HANDLE_IRQ, [], async {
+            // Read interrupt status
+            let interrupt_status = self.read_mmio(VIRTIO_MMIO_INTERRUPT_STATUS);
+
+            if interrupt_status != 0 {
+                // Process queue completions
+                self.process_queue_completions().await?;
+
+                // Acknowledge interrupt
+                self.write_mmio(VIRTIO_MMIO_INTERRUPT_ACK, interrupt_status);
+            }
+
+            Ok(())
+        })
     }
 
     async fn process_queue_completions(&mut self) -> DriverResult<()> {
@@ -1239,6 +1262,15 @@ impl OrionDriver for VirtioBlockDriver {
                         let bytes_written = self.write_blocks(0, 1, &buffer).await?;
                         Ok(bytes_written)
                     }
+                    orion_driver::IoRequestType::Ioctl if io_msg.length == BLK_IOCTL_CONTROL => {
+                        // Raw disk requests answer with data rather than a length
+                        let reply = self.block_control(&io_msg.data).await;
+                        return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                    }
+                    orion_driver::IoRequestType::Ioctl if io_msg.length == PROBE_IOCTL_CONTROL => {
+                        let reply = orion_probe::handle_control(&PROBES, &io_msg.data);
+                        return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                    }
                     orion_driver::IoRequestType::Ioctl => {
                         // Handle ioctl request
                         Ok(0)
@@ -1269,27 +1301,31 @@ impl OrionDriver for VirtioBlockDriver {
 
 impl BlockDriver for VirtioBlockDriver {
     async fn read_blocks(&mut self, lba: u64, count: u32, buffer: &mut [u8]) -> DriverResult<usize> {
-        if !self.device_ready {
-            return Err(DriverError::IoError);
-        }
+        probe!(PROBES, BLOCK_READ_BLOCKS, [lba, count, buffer], async {
+            if !self.device_ready {
+                return Err(DriverError::IoError);
+            }
 
-        if buffer.len() < (count * self.config.blk_size) as usize {
-            return Err(DriverError::IoError);
-        }
+            if buffer.len() < (count * self.config.blk_size) as usize {
+                return Err(DriverError::IoError);
+            }
 
-        self.read_blocks_virtio(lba, count, buffer).await
+            self.read_blocks_virtio(lba, count, buffer).await
+        })
     }
 
     async fn write_blocks(&mut self, lba: u64, count: u32, buffer: &[u8]) -> DriverResult<usize> {
-        if !self.device_ready {
-            return Err(DriverError::IoError);
-        }
+        probe!(PROBES, BLOCK_WRITE_BLOCKS, [lba, count, buffer], async {
+            if !self.device_ready {
+                return Err(DriverError::IoError);
+            }
 
-        if buffer.len() < (count * self.config.blk_size) as usize {
-            return Err(DriverError::IoError);
-        }
+            if buffer.len() < (count * self.config.blk_size) as usize {
+                return Err(DriverError::IoError);
+            }
 
-        self.write_blocks_virtio(lba, count, buffer).await
+            self.write_blocks_virtio(lba, count, buffer).await
+        })
     }
 
     async fn get_capacity(&self) -> DriverResult<u64> {
@@ -1979,6 +2015,54 @@ impl VirtioBlockDriver {
         self.flush_cache().await
     }
 
+    /// Serve a raw disk control request (see orion_blkio::control)
+    pub async fn block_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let block_size = self.config.blk_size;
+        let read_only = (self.features & VIRTIO_BLK_F_RO) != 0;
+        let result = match ControlRequest::decode(request, block_size) {
+            None => Err(STATUS_EINVAL),
+            Some(ControlRequest::Info) => {
+                let flags = if read_only { BLK_INFO_READ_ONLY } else { 0 };
+                // Capacity is counted in 512 byte sectors whatever the block size
+                let blocks = self.config.capacity * 512 / block_size as u64;
+                Ok(encode_info(&DiskInfo { block_size, flags, blocks }))
+            }
+            Some(ControlRequest::Read { lba, count }) => {
+                let mut buffer = vec![0u8; (count * block_size) as usize];
+                match self.read_blocks(lba, count, &mut buffer).await {
+                    Ok(_) => Ok(buffer),
+                    Err(_) => Err(STATUS_EIO),
+                }
+            }
+            Some(ControlRequest::Write { .. }) if read_only => Err(STATUS_EROFS),
+            Some(ControlRequest::Write { lba, data }) => {
+                let count = data.len() as u32 / block_size;
+                self.write_blocks(lba, count, data).await.map(|_| Vec::new()).map_err(|_| STATUS_EIO)
+            }
+            Some(ControlRequest::Flush) => self.flush_cache().await.map(|_| Vec::new()).map_err(|_| STATUS_EIO),
+            Some(ControlRequest::ReadIntegrity { lba, count, algorithm }) => {
+                let mut buffer = vec![0u8; (count * block_size) as usize];
+                match self.read_blocks(lba, count, &mut buffer).await {
+                    Ok(_) => Ok(encode_integrity_read(algorithm, block_size, &buffer)),
+                    Err(_) => Err(STATUS_EIO),
+                }
+            }
+            Some(ControlRequest::WriteIntegrity { .. }) if read_only => Err(STATUS_EROFS),
+            Some(ControlRequest::WriteIntegrity { lba, checksums, data }) => {
+                if checksums.verify(data).is_err() {
+                    Err(STATUS_EBADMSG)
+                } else {
+                    let count = data.len() as u32 / block_size;
+                    self.write_blocks(lba, count, data).await.map(|_| Vec::new()).map_err(|_| STATUS_EIO)
+                }
+            }
+        };
+        match result {
+            Ok(payload) => reply(STATUS_OK, &payload),
+            Err(status) => reply(status, &[]),
+        }
+    }
+
     /// Get device configuration
     pub async fn get_device_config(&self) -> DriverResult<VirtioBlockConfig> {
         if !self.device_ready {
@@ -0,0 +1,353 @@
+/*
+ * Orion Operating System - Compressed RAM Block Driver
+ *
+ * Exposes an orion_zram device as a block device, so it can be handed to
+ * the swap code or used as the backing store of a tmpfs. Sectors live in
+ * memory compressed a page at a time with LZ4; zero and same-filled pages
+ * are only recorded, and trims give the memory of whole pages back. The
+ * driver adds the block layer around it: request validation, the usual
+ * latency statistics, and the compression figures (data held, memory
+ * used, ratio and savings) for monitoring tools.
+ *
+ * Nothing is persistent, so flush has nothing to do and the device reads
+ * back as zeros after every boot.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+#![feature(async_fn_in_trait)]
+
+extern crate alloc;
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use orion_sys::{clock_get, nanosleep};
+use orion_driver::{
+    OrionDriver, BlockDriver, DeviceInfo, DriverError, DriverResult,
+    MessageLoop, PowerState, DeviceState, HotplugEvent,
+};
+use orion_block::{BlockDevice, BlockStats};
+use orion_blockstats::{BlockStatistics, BlockStatsSource, Operation};
+use orion_zram::{Zram, ZramError, ZramStats, SECTOR_SIZE};
+
+/// Size of the device created at boot
+pub const DEFAULT_DISK_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Compressed RAM block device
+pub struct ZramDriver {
+    /// Device information
+    info: DeviceInfo,
+    /// Driver state
+    state: DeviceState,
+    /// Power state
+    power_state: PowerState,
+    /// Pages and compression statistics
+    device: Zram,
+    /// Latency histograms and throughput per operation type
+    io: BlockStatistics,
+    /// Message loop
+    message_loop: MessageLoop,
+}
+
+impl ZramDriver {
+    /// A driver for a device of `disk_size` bytes, rounded up to pages
+    pub fn new(disk_size: u64) -> Self {
+        Self {
+            info: DeviceInfo {
+                name: "zram".to_string(),
+                version: "1.0.0".to_string(),
+                description: "Compressed RAM Block Device Driver".to_string(),
+                vendor: "ORION OS".to_string(),
+                device_type: "block".to_string(),
+                capabilities: vec![
+                    "compression".to_string(),
+                    "swap".to_string(),
+                    "trim".to_string(),
+                    "zero_page_dedup".to_string(),
+                ],
+            },
+            state: DeviceState::Initializing,
+            power_state: PowerState::Active,
+            device: Zram::new(disk_size),
+            io: BlockStatistics::new(),
+            message_loop: MessageLoop::new(),
+        }
+    }
+
+    /// Initialize the zram driver
+    pub async fn initialize(&mut self) -> DriverResult<()> {
+        self.state = DeviceState::Initializing;
+        self.message_loop.start().await?;
+        self.state = DeviceState::Ready;
+        Ok(())
+    }
+
+    /// Cap the memory the device may use for page storage, 0 for no cap
+    pub fn set_mem_limit(&mut self, bytes: u64) {
+        self.device.set_mem_limit(bytes);
+    }
+
+    /// Compression statistics: data held, memory used, ratio and savings
+    pub fn zram_stats(&self) -> ZramStats {
+        self.device.stats()
+    }
+
+    /// Record the outcome of one request and convert its error
+    fn complete<T>(&self, operation: Operation, bytes: u64, start: u64, result: Result<T, ZramError>) -> DriverResult<T> {
+        match result {
+            Ok(value) => {
+                self.io.record(operation, bytes, monotonic_ns().saturating_sub(start));
+                Ok(value)
+            }
+            Err(error) => {
+                self.io.record_error(operation);
+                Err(match error {
+                    ZramError::OutOfRange | ZramError::Unaligned => DriverError::InvalidParameter,
+                    ZramError::MemoryLimit => DriverError::OutOfMemory,
+                    ZramError::Corrupt => DriverError::IoError,
+                })
+            }
+        }
+    }
+
+    async fn handle_io_message(&mut self, message: orion_driver::IoMessage) -> DriverResult<orion_driver::IoResponse> {
+        match message {
+            orion_driver::IoMessage::Read { offset, length } => {
+                let data = self.read(offset, length).await?;
+                Ok(orion_driver::IoResponse::Read { data })
+            }
+            orion_driver::IoMessage::Write { offset, data } => {
+                let bytes_written = self.write(offset, &data).await?;
+                Ok(orion_driver::IoResponse::Write { bytes_written })
+            }
+            orion_driver::IoMessage::Trim { offset, length } => {
+                let bytes_trimmed = self.trim(offset, length).await?;
+                Ok(orion_driver::IoResponse::Trim { bytes_trimmed })
+            }
+            orion_driver::IoMessage::Flush => {
+                self.flush().await?;
+                Ok(orion_driver::IoResponse::Flush)
+            }
+        }
+    }
+}
+
+impl OrionDriver for ZramDriver {
+    async fn initialize(&mut self) -> DriverResult<()> {
+        self.initialize().await
+    }
+
+    async fn shutdown(&mut self) -> DriverResult<()> {
+        self.state = DeviceState::ShuttingDown;
+        self.message_loop.stop().await?;
+        self.state = DeviceState::Shutdown;
+        Ok(())
+    }
+
+    async fn get_info(&self) -> DriverResult<DeviceInfo> {
+        Ok(self.info.clone())
+    }
+
+    async fn get_version(&self) -> DriverResult<String> {
+        Ok(self.info.version.clone())
+    }
+
+    async fn can_handle(&self, device: &str) -> DriverResult<bool> {
+        Ok(device.starts_with("zram"))
+    }
+
+    async fn get_state(&self) -> DriverResult<DeviceState> {
+        Ok(self.state.clone())
+    }
+
+    async fn set_state(&mut self, state: DeviceState) -> DriverResult<()> {
+        self.state = state;
+        Ok(())
+    }
+
+    async fn get_power_state(&self) -> DriverResult<PowerState> {
+        Ok(self.power_state.clone())
+    }
+
+    async fn set_power_state(&mut self, power_state: PowerState) -> DriverResult<()> {
+        self.power_state = power_state;
+        Ok(())
+    }
+
+    async fn handle_hotplug(&mut self, _event: HotplugEvent) -> DriverResult<()> {
+        // A RAM device has no hardware to come or go
+        Ok(())
+    }
+
+    async fn run_message_loop(&mut self) -> DriverResult<()> {
+        self.message_loop.run().await
+    }
+}
+
+impl BlockDriver for ZramDriver {
+    async fn read(&mut self, offset: u64, length: u64) -> DriverResult<Vec<u8>> {
+        let start = monotonic_ns();
+        let mut data = vec![0u8; length as usize];
+        let result = self.device.read(offset, &mut data);
+        self.complete(Operation::Read, length, start, result)?;
+        Ok(data)
+    }
+
+    async fn write(&mut self, offset: u64, data: &[u8]) -> DriverResult<u64> {
+        let start = monotonic_ns();
+        let result = self.device.write(offset, data);
+        self.complete(Operation::Write, data.len() as u64, start, result)?;
+        Ok(data.len() as u64)
+    }
+
+    async fn trim(&mut self, offset: u64, length: u64) -> DriverResult<u64> {
+        let start = monotonic_ns();
+        let result = self.device.discard(offset, length);
+        self.complete(Operation::Trim, length, start, result)?;
+        Ok(length)
+    }
+
+    async fn flush(&mut self) -> DriverResult<()> {
+        self.io.record(Operation::Flush, 0, 0);
+        Ok(())
+    }
+
+    async fn get_device_info(&mut self) -> DriverResult<BlockDevice> {
+        Ok(BlockDevice {
+            name: "zram0".to_string(),
+            size: self.device.disk_size(),
+            block_size: SECTOR_SIZE as u32,
+            read_only: false,
+            supports_trim: true,
+            supports_flush: true,
+            supports_write_zeroes: false,
+            supports_block_status: false,
+        })
+    }
+
+    async fn get_block_stats(&mut self) -> DriverResult<BlockStats> {
+        let report = self.io.report();
+        Ok(BlockStats {
+            bytes_read: report.read.bytes,
+            bytes_written: report.write.bytes,
+            total_operations: report.total_operations(),
+            read_operations: report.read.operations,
+            write_operations: report.write.operations,
+            trim_operations: report.trim.operations,
+            flush_operations: report.flush.operations,
+            error_count: report.total_errors(),
+            avg_latency: report.latency.mean,
+            max_latency: report.latency.max,
+            min_latency: report.latency.min,
+        })
+    }
+
+    // Pages are already in memory; there is nothing to cache
+    async fn cache_get(&mut self, _offset: u64, _length: u64) -> DriverResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn cache_put(&mut self, _offset: u64, _data: &[u8]) -> DriverResult<()> {
+        Ok(())
+    }
+
+    async fn cache_trim(&mut self, _offset: u64, _length: u64) -> DriverResult<()> {
+        Ok(())
+    }
+
+    async fn cache_flush(&mut self) -> DriverResult<()> {
+        Ok(())
+    }
+}
+
+impl BlockStatsSource for ZramDriver {
+    fn block_statistics(&self) -> &BlockStatistics {
+        &self.io
+    }
+}
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn idle_ns(duration: u64) {
+    let _ = nanosleep(duration);
+}
+
+// Main driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() -> i32 {
+    let mut driver = ZramDriver::new(DEFAULT_DISK_SIZE);
+
+    orion_async::set_clock(monotonic_ns);
+    orion_async::set_idle(idle_ns);
+    match orion_async::block_on(driver.initialize()) {
+        Ok(_) => {
+            println!("zram: {} MiB compressed RAM device ready", driver.device.disk_size() >> 20);
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to initialize zram driver: {:?}", e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_zram::PAGE_SIZE;
+
+    #[tokio::test]
+    async fn test_zram_pages_round_trip() {
+        let mut driver = ZramDriver::new(1024 * 1024);
+        assert!(driver.initialize().await.is_ok());
+
+        let page: Vec<u8> = b"orion zram ".iter().cycle().take(PAGE_SIZE).copied().collect();
+        assert_eq!(driver.write(0, &page).await.unwrap(), PAGE_SIZE as u64);
+        assert_eq!(driver.read(0, PAGE_SIZE as u64).await.unwrap(), page);
+        assert_eq!(driver.read(PAGE_SIZE as u64, 512).await.unwrap(), vec![0u8; 512]);
+
+        let stats = driver.get_block_stats().await.unwrap();
+        assert_eq!(stats.write_operations, 1);
+        assert_eq!(stats.read_operations, 2);
+    }
+
+    #[tokio::test]
+    async fn test_zram_reports_savings() {
+        let mut driver = ZramDriver::new(1024 * 1024);
+        for index in 0..8 {
+            driver.write(index * PAGE_SIZE as u64, &[0u8; PAGE_SIZE]).await.unwrap();
+        }
+        let stats = driver.zram_stats();
+        assert_eq!(stats.zero_pages, 8);
+        assert_eq!(stats.compr_data_size, 0);
+        assert!(stats.memory_saved() > 0);
+
+        assert_eq!(driver.trim(0, 4 * PAGE_SIZE as u64).await.unwrap(), 4 * PAGE_SIZE as u64);
+        assert_eq!(driver.zram_stats().zero_pages, 4);
+    }
+
+    #[tokio::test]
+    async fn test_zram_rejects_bad_requests() {
+        let mut driver = ZramDriver::new(1024 * 1024);
+        assert!(matches!(driver.read(100, 512).await, Err(DriverError::InvalidParameter)));
+        assert!(matches!(driver.write(1024 * 1024, &[0u8; 512]).await, Err(DriverError::InvalidParameter)));
+
+        driver.set_mem_limit(1);
+        let text: Vec<u8> = b"swap ".iter().cycle().take(PAGE_SIZE).copied().collect();
+        assert!(matches!(driver.write(0, &text).await, Err(DriverError::OutOfMemory)));
+        assert_eq!(driver.get_block_stats().await.unwrap().error_count, 3);
+    }
+}
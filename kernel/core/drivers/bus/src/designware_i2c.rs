@@ -0,0 +1,578 @@
+/*
+ * Orion Operating System - Synopsys DesignWare I2C Driver
+ *
+ * Driver for the DesignWare I2C controller, the I2C master of Intel's
+ * LPSS (the touchpads, sensors and audio codecs of laptops) and of many
+ * ARM SoCs. The controller sends any I2C message, so the adapter runs
+ * plain transfers and the SMBus operations are emulated on them (see
+ * lib/orion_i2c).
+ *
+ * On Intel platforms the controllers are PCI functions 00:15.0-3 and
+ * 00:19.0-1, their registers behind BAR 0 and the LPSS private registers
+ * 0x200 further. The driver serves every controller it finds on its own
+ * IPC endpoint; a request starts with the index of the controller,
+ * `bus: u32`, and the orion_i2c control request follows. Devices come
+ * from the firmware enumeration through ADD_DEVICE. The SCL timings are
+ * left as the firmware programmed them for the board.
+ *
+ * A transfer keeps the transmit FIFO fed with the bytes to write and the
+ * read commands, and drains the receive FIFO as it goes; completion and
+ * aborts are polled from the raw interrupt status.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_driver::{DriverError, DriverResult, MmioAccessor, MmioPermissions};
+use orion_i2c::control::{I2C_CTRL_ADD_DEVICE, I2C_CTRL_SMBUS, I2C_CTRL_TRANSFER};
+use orion_i2c::{
+    handle_control, Functionality, I2cAdapter, I2cBus, I2cError, I2cMessage, I2C_DEVICE_DRIVERS, I2C_M_RECV_LEN,
+    I2C_M_TEN, SMBUS_BLOCK_MAX,
+};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::rights::CAP_ADMIN;
+use orion_sys::nanosleep;
+
+// Global allocator for the driver
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the buses and their devices
+const HEAP_SIZE: usize = 256 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+// PCI configuration mechanism #1
+const PCI_CONFIG_ADDRESS: u16 = 0xCF8;
+const PCI_CONFIG_DATA: u16 = 0xCFC;
+
+/// Where the LPSS I2C controllers are, as device and function on bus 0
+const LPSS_I2C_LOCATIONS: &[(u8, u8)] = &[(0x15, 0), (0x15, 1), (0x15, 2), (0x15, 3), (0x19, 0), (0x19, 1)];
+
+const PCI_VENDOR_INTEL: u16 = 0x8086;
+
+/// LPSS I2C controllers of the chipsets the driver knows
+const LPSS_I2C_DEVICE_IDS: &[u16] = &[
+    0x9D60, 0x9D61, 0x9D62, 0x9D63, 0x9D64, 0x9D65, // Sunrise Point-LP
+    0xA160, 0xA161, 0xA162, // Sunrise Point-H
+    0x9DE8, 0x9DE9, 0x9DEA, 0x9DEB, 0x9DC5, 0x9DC6, // Cannon Lake-LP
+    0xA0E8, 0xA0E9, 0xA0EA, 0xA0EB, 0xA0C5, 0xA0C6, // Tiger Lake-LP
+    0x51E8, 0x51E9, 0x51EA, 0x51EB, 0x51C5, 0x51C6, // Alder Lake-P
+    0x7ACC, 0x7ACD, 0x7ACE, 0x7ACF, 0x7AFC, 0x7AFD, // Alder Lake-S
+];
+
+// PCI configuration registers
+const PCI_ID: u8 = 0x00;
+const PCI_COMMAND: u8 = 0x04;
+const PCI_BAR0: u8 = 0x10;
+const PCI_BAR0_HIGH: u8 = 0x14;
+
+const PCI_COMMAND_MEMORY: u32 = 0x0002;
+const PCI_BAR_MEMORY_MASK: u32 = 0xFFFF_FFF0;
+const PCI_BAR_64BIT: u32 = 0x04;
+
+/// Registers of the controller and the LPSS private ones
+const DW_WINDOW_SIZE: usize = 0x1000;
+
+// LPSS private registers: the controller is held in reset until released
+const LPSS_PRIV_RESETS: u64 = 0x204;
+const LPSS_RESETS_RELEASE: u32 = 0x07;
+
+// Controller registers
+const IC_CON: u64 = 0x00;
+const IC_TAR: u64 = 0x04;
+const IC_DATA_CMD: u64 = 0x10;
+const IC_INTR_MASK: u64 = 0x30;
+const IC_RAW_INTR_STAT: u64 = 0x34;
+const IC_CLR_INTR: u64 = 0x40;
+const IC_CLR_TX_ABRT: u64 = 0x54;
+const IC_CLR_STOP_DET: u64 = 0x60;
+const IC_ENABLE: u64 = 0x6C;
+const IC_STATUS: u64 = 0x70;
+const IC_TXFLR: u64 = 0x74;
+const IC_RXFLR: u64 = 0x78;
+const IC_TX_ABRT_SOURCE: u64 = 0x80;
+const IC_ENABLE_STATUS: u64 = 0x9C;
+const IC_COMP_PARAM_1: u64 = 0xF4;
+const IC_COMP_TYPE: u64 = 0xFC;
+
+const DW_COMP_TYPE: u32 = 0x4457_0140;
+
+// IC_CON: master at fast speed, restarts allowed
+const CON_MASTER_MODE: u32 = 0x01;
+const CON_SPEED_FAST: u32 = 0x04;
+const CON_10BITADDR_MASTER: u32 = 0x10;
+const CON_RESTART_EN: u32 = 0x20;
+const CON_SLAVE_DISABLE: u32 = 0x40;
+
+// IC_DATA_CMD: the byte, and what to do with it
+const CMD_READ: u32 = 0x100;
+const CMD_STOP: u32 = 0x200;
+const CMD_RESTART: u32 = 0x400;
+
+// IC_RAW_INTR_STAT
+const INTR_TX_ABRT: u32 = 0x040;
+const INTR_STOP_DET: u32 = 0x200;
+
+const STATUS_ACTIVITY: u32 = 0x01;
+
+// IC_TX_ABRT_SOURCE
+const ABRT_NOACK: u32 = 0x0F; // 7-bit or 10-bit address, or data
+const ABRT_ARB_LOST: u32 = 0x1000;
+
+/// Checks while waiting, DW_POLL_NS apart, before giving up: 25 ms
+const DW_POLL_NS: u64 = 10_000;
+const DW_MAX_POLLS: u32 = 2_500;
+
+/// FIFO depth of controllers not telling it
+const DW_DEFAULT_FIFO_DEPTH: u32 = 32;
+
+const STATUS_ENODEV: i32 = -19;
+
+// ========================================
+// HARDWARE ACCESS
+// ========================================
+
+unsafe fn inl(port: u16) -> u32 {
+    let value: u32;
+    core::arch::asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn outl(port: u16, value: u32) {
+    core::arch::asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags));
+}
+
+fn pci_config_read((device, function): (u8, u8), offset: u8) -> u32 {
+    let address = 0x8000_0000 | (device as u32) << 11 | (function as u32) << 8 | (offset & 0xFC) as u32;
+    unsafe {
+        outl(PCI_CONFIG_ADDRESS, address);
+        inl(PCI_CONFIG_DATA)
+    }
+}
+
+fn pci_config_write((device, function): (u8, u8), offset: u8, value: u32) {
+    let address = 0x8000_0000 | (device as u32) << 11 | (function as u32) << 8 | (offset & 0xFC) as u32;
+    unsafe {
+        outl(PCI_CONFIG_ADDRESS, address);
+        outl(PCI_CONFIG_DATA, value);
+    }
+}
+
+/// The registers of the LPSS I2C controller at `location`, out of reset
+fn enable_controller(location: (u8, u8)) -> Option<MmioAccessor> {
+    let id = pci_config_read(location, PCI_ID);
+    if id as u16 != PCI_VENDOR_INTEL || !LPSS_I2C_DEVICE_IDS.contains(&((id >> 16) as u16)) {
+        return None;
+    }
+    let bar = pci_config_read(location, PCI_BAR0);
+    let mut base = (bar & PCI_BAR_MEMORY_MASK) as u64;
+    if bar & PCI_BAR_64BIT != 0 {
+        base |= (pci_config_read(location, PCI_BAR0_HIGH) as u64) << 32;
+    }
+    if base == 0 {
+        return None;
+    }
+    let command = pci_config_read(location, PCI_COMMAND) & 0xFFFF;
+    pci_config_write(location, PCI_COMMAND, command | PCI_COMMAND_MEMORY);
+    let mmio = unsafe {
+        MmioAccessor::new(base, DW_WINDOW_SIZE, MmioPermissions::READ | MmioPermissions::WRITE | MmioPermissions::UNCACHED)
+    };
+    mmio.write_u32(LPSS_PRIV_RESETS, LPSS_RESETS_RELEASE).ok()?;
+    Some(mmio)
+}
+
+/// Register access, abstracted so the driver runs against a simulated
+/// controller in the tests
+pub trait DwRegisters {
+    fn read(&mut self, register: u64) -> u32;
+    fn write(&mut self, register: u64, value: u32);
+}
+
+impl DwRegisters for MmioAccessor {
+    fn read(&mut self, register: u64) -> u32 {
+        self.read_u32(register).unwrap_or(u32::MAX)
+    }
+
+    fn write(&mut self, register: u64, value: u32) {
+        let _ = self.write_u32(register, value);
+    }
+}
+
+// ========================================
+// ADAPTER
+// ========================================
+
+pub struct DesignWare<R: DwRegisters> {
+    registers: R,
+    tx_fifo_depth: u32,
+}
+
+impl<R: DwRegisters> DesignWare<R> {
+    /// Check the controller is a DesignWare one and quiet it
+    pub fn probe(mut registers: R) -> DriverResult<Self> {
+        if registers.read(IC_COMP_TYPE) != DW_COMP_TYPE {
+            return Err(DriverError::DeviceNotFound);
+        }
+        let tx_fifo_depth = match registers.read(IC_COMP_PARAM_1) {
+            0 => DW_DEFAULT_FIFO_DEPTH,
+            param => ((param >> 16) & 0xFF) + 1,
+        };
+        let mut controller = Self { registers, tx_fifo_depth };
+        controller.disable()?;
+        controller.registers.write(IC_INTR_MASK, 0);
+        let _ = controller.registers.read(IC_CLR_INTR);
+        Ok(controller)
+    }
+
+    /// Wait until `done`, or give up after DW_MAX_POLLS checks
+    fn poll(&mut self, mut done: impl FnMut(&mut R) -> bool) -> Result<(), I2cError> {
+        for _ in 0..DW_MAX_POLLS {
+            if done(&mut self.registers) {
+                return Ok(());
+            }
+            let _ = nanosleep(DW_POLL_NS);
+        }
+        Err(I2cError::Timeout)
+    }
+
+    fn disable(&mut self) -> DriverResult<()> {
+        self.registers.write(IC_ENABLE, 0);
+        self.poll(|registers| registers.read(IC_ENABLE_STATUS) & 1 == 0).map_err(|_| DriverError::Timeout)
+    }
+
+    /// Address `address` for the next transaction
+    fn start(&mut self, address: u16, ten_bit: bool) -> Result<(), I2cError> {
+        self.disable().map_err(|_| I2cError::Timeout)?;
+        let mut con = CON_MASTER_MODE | CON_SPEED_FAST | CON_RESTART_EN | CON_SLAVE_DISABLE;
+        if ten_bit {
+            con |= CON_10BITADDR_MASTER;
+        }
+        self.registers.write(IC_CON, con);
+        self.registers.write(IC_TAR, address as u32);
+        let _ = self.registers.read(IC_CLR_INTR);
+        self.registers.write(IC_ENABLE, 1);
+        Ok(())
+    }
+
+    /// The error of an aborted transaction, if it was
+    fn aborted(&mut self) -> Result<(), I2cError> {
+        if self.registers.read(IC_RAW_INTR_STAT) & INTR_TX_ABRT == 0 {
+            return Ok(());
+        }
+        let source = self.registers.read(IC_TX_ABRT_SOURCE);
+        let _ = self.registers.read(IC_CLR_TX_ABRT);
+        Err(if source & ABRT_NOACK != 0 {
+            I2cError::NoAck
+        } else if source & ABRT_ARB_LOST != 0 {
+            I2cError::ArbitrationLost
+        } else {
+            I2cError::BusError
+        })
+    }
+
+    /// Queue a command once the transmit FIFO has room
+    fn push(&mut self, command: u32) -> Result<(), I2cError> {
+        let depth = self.tx_fifo_depth;
+        self.poll(|registers| registers.read(IC_TXFLR) < depth || registers.read(IC_RAW_INTR_STAT) & INTR_TX_ABRT != 0)?;
+        self.aborted()?;
+        self.registers.write(IC_DATA_CMD, command);
+        Ok(())
+    }
+
+    /// Run the messages; the controller is enabled on their address
+    fn run(&mut self, messages: &mut [I2cMessage]) -> Result<(), I2cError> {
+        let count = messages.len();
+        for (index, message) in messages.iter_mut().enumerate() {
+            let last = index + 1 == count;
+            let first_flag = if index > 0 { CMD_RESTART } else { 0 };
+            if !message.is_read() {
+                let length = message.data.len();
+                for position in 0..length {
+                    let mut command = message.data[position] as u32;
+                    if position == 0 {
+                        command |= first_flag;
+                    }
+                    if last && position + 1 == length {
+                        command |= CMD_STOP;
+                    }
+                    self.push(command)?;
+                }
+                continue;
+            }
+
+            // With I2C_M_RECV_LEN the first byte tells how many follow
+            let recv_len = message.flags & I2C_M_RECV_LEN != 0;
+            let mut length = if recv_len { 1 } else { message.data.len() };
+            let (mut issued, mut received) = (0, 0);
+            while received < length {
+                while issued < length && issued - received < self.tx_fifo_depth as usize {
+                    let mut command = CMD_READ;
+                    if issued == 0 {
+                        command |= first_flag;
+                    }
+                    if last && issued + 1 == length && !(recv_len && received == 0) {
+                        command |= CMD_STOP;
+                    }
+                    self.push(command)?;
+                    issued += 1;
+                }
+                self.poll(|registers| {
+                    registers.read(IC_RXFLR) > 0 || registers.read(IC_RAW_INTR_STAT) & INTR_TX_ABRT != 0
+                })?;
+                self.aborted()?;
+                while received < length && self.registers.read(IC_RXFLR) > 0 {
+                    let byte = self.registers.read(IC_DATA_CMD) as u8;
+                    message.data[received] = byte;
+                    received += 1;
+                    if recv_len && received == 1 {
+                        if byte == 0 || byte as usize > SMBUS_BLOCK_MAX {
+                            return Err(I2cError::BusError);
+                        }
+                        length = 1 + byte as usize;
+                    }
+                }
+            }
+            message.data.truncate(length);
+        }
+
+        self.poll(|registers| registers.read(IC_RAW_INTR_STAT) & (INTR_STOP_DET | INTR_TX_ABRT) != 0)?;
+        self.aborted()?;
+        let _ = self.registers.read(IC_CLR_STOP_DET);
+        Ok(())
+    }
+}
+
+impl<R: DwRegisters> I2cAdapter for DesignWare<R> {
+    /// Zero-length messages cannot be sent, so no quick commands
+    fn functionality(&self) -> Functionality {
+        Functionality::I2C | Functionality::TEN_BIT | Functionality::RECV_LEN | Functionality::SMBUS_EMULATED
+    }
+
+    fn transfer(&mut self, messages: &mut [I2cMessage]) -> Result<(), I2cError> {
+        let target = &messages[0];
+        let (address, ten_bit) = (target.address, target.flags & I2C_M_TEN != 0);
+        // The target is fixed while the controller is enabled
+        if messages.iter().any(|message| {
+            message.address != address || (message.flags & I2C_M_TEN != 0) != ten_bit || message.data.is_empty()
+        }) {
+            return Err(I2cError::NotSupported);
+        }
+        self.start(address, ten_bit)?;
+        let result = self.run(messages);
+        if result.is_err() {
+            // Disabling stops a transaction still on the bus
+            let _ = self.poll(|registers| registers.read(IC_STATUS) & STATUS_ACTIVITY == 0);
+        }
+        let _ = self.disable();
+        result
+    }
+}
+
+// ========================================
+// I2C SERVICE
+// ========================================
+
+struct I2cServer {
+    buses: Vec<I2cBus<DesignWare<MmioAccessor>>>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl I2cServer {
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let bus = read_u32(&message.data, 0).and_then(|index| self.buses.get_mut(index as usize));
+        let Some(bus) = bus else {
+            self.ipc_channel.send(message.sender, &STATUS_ENODEV.to_le_bytes());
+            return;
+        };
+        let request = &message.data[4..];
+        // Listing a bus is open; reaching the devices is not
+        let privileged =
+            matches!(read_u32(request, 0), Some(I2C_CTRL_TRANSFER | I2C_CTRL_SMBUS | I2C_CTRL_ADD_DEVICE));
+        let admin = privileged && self.capabilities.check_rights(message.capability, CAP_ADMIN, message.sender);
+        let response = handle_control(bus, request, admin);
+        self.ipc_channel.send(message.sender, &response);
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let buses: Vec<_> = LPSS_I2C_LOCATIONS
+        .iter()
+        .filter_map(|&location| enable_controller(location))
+        .filter_map(|mmio| DesignWare::probe(mmio).ok())
+        .map(|controller| I2cBus::new(controller, Vec::new(), I2C_DEVICE_DRIVERS))
+        .collect();
+    if buses.is_empty() {
+        return;
+    }
+    let mut server = I2cServer { buses, ipc_channel: IpcChannel::new(), capabilities: Capability::new() };
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+    use orion_i2c::{i2c_transfer, smbus_transfer, SmbusData, SmbusOp, I2C_M_RD};
+
+    /// A controller with an EEPROM at 0x50 behind it: the first byte
+    /// written after a start sets the pointer, reads return from there
+    struct FakeDw {
+        enabled: bool,
+        target: u32,
+        raw: u32,
+        abort_source: u32,
+        rx: VecDeque<u8>,
+        memory: [u8; 256],
+        pointer: u8,
+        /// Whether the next written byte is the first of a message
+        addressing: bool,
+        stops: usize,
+    }
+
+    impl FakeDw {
+        fn new() -> Self {
+            let mut memory = [0; 256];
+            memory[0x80..0x83].copy_from_slice(&[2, 0x12, 0x34]);
+            Self {
+                enabled: false,
+                target: 0,
+                raw: 0,
+                abort_source: 0,
+                rx: VecDeque::new(),
+                memory,
+                pointer: 0,
+                addressing: true,
+                stops: 0,
+            }
+        }
+
+        fn command(&mut self, value: u32) {
+            if self.target != 0x50 {
+                self.raw |= INTR_TX_ABRT | INTR_STOP_DET;
+                self.abort_source = 0x1;
+                return;
+            }
+            if value & CMD_RESTART != 0 {
+                self.addressing = true;
+            }
+            if value & CMD_READ != 0 {
+                self.rx.push_back(self.memory[self.pointer as usize]);
+                self.pointer = self.pointer.wrapping_add(1);
+            } else if self.addressing {
+                self.pointer = value as u8;
+                self.addressing = false;
+            } else {
+                self.memory[self.pointer as usize] = value as u8;
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            if value & CMD_STOP != 0 {
+                self.raw |= INTR_STOP_DET;
+                self.addressing = true;
+                self.stops += 1;
+            }
+        }
+    }
+
+    impl DwRegisters for FakeDw {
+        fn read(&mut self, register: u64) -> u32 {
+            match register {
+                IC_COMP_TYPE => DW_COMP_TYPE,
+                IC_COMP_PARAM_1 => 0x0003_0300, // 4-entry FIFOs
+                IC_ENABLE_STATUS => self.enabled as u32,
+                IC_RAW_INTR_STAT => self.raw,
+                IC_TX_ABRT_SOURCE => self.abort_source,
+                IC_CLR_TX_ABRT => {
+                    self.raw &= !INTR_TX_ABRT;
+                    0
+                }
+                IC_CLR_STOP_DET => {
+                    self.raw &= !INTR_STOP_DET;
+                    0
+                }
+                IC_CLR_INTR => {
+                    self.raw = 0;
+                    0
+                }
+                IC_RXFLR => self.rx.len() as u32,
+                IC_DATA_CMD => self.rx.pop_front().unwrap_or(0) as u32,
+                _ => 0,
+            }
+        }
+
+        fn write(&mut self, register: u64, value: u32) {
+            match register {
+                IC_ENABLE => self.enabled = value & 1 != 0,
+                IC_TAR => self.target = value,
+                IC_DATA_CMD if self.enabled => self.command(value),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_runs_transfers() {
+        let mut controller = DesignWare::probe(FakeDw::new()).unwrap();
+        assert_eq!(controller.tx_fifo_depth, 4);
+
+        // More bytes than the FIFO holds, then read them back
+        let data = [0x10, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        i2c_transfer(&mut controller, &mut [I2cMessage::write(0x50, &data)]).unwrap();
+        let mut messages = [I2cMessage::write(0x50, &[0x10]), I2cMessage::read(0x50, 9)];
+        i2c_transfer(&mut controller, &mut messages).unwrap();
+        assert_eq!(messages[1].data, data[1..]);
+        assert_eq!((controller.registers.stops, controller.registers.enabled), (2, false));
+
+        // Block reads learn their length from the device
+        let block = smbus_transfer(&mut controller, 0x50, &SmbusOp::ReadBlockData(0x80));
+        assert_eq!(block, Ok(SmbusData::Block(vec![0x12, 0x34])));
+
+        assert_eq!(smbus_transfer(&mut controller, 0x51, &SmbusOp::ReadByte), Err(I2cError::NoAck));
+        let mut mixed = [I2cMessage::write(0x50, &[0]), I2cMessage { address: 0x51, flags: I2C_M_RD, data: vec![0] }];
+        assert_eq!(i2c_transfer(&mut controller, &mut mixed), Err(I2cError::NotSupported));
+    }
+}
@@ -0,0 +1,529 @@
+/*
+ * Orion Operating System - Intel i801 SMBus Driver
+ *
+ * Driver for the SMBus host controller of Intel chipsets, from the ICH
+ * to current PCHs and the ICH9 of QEMU's q35 machine. It is the PC's
+ * board management bus: the SPD EEPROMs of the memory modules, their
+ * temperature sensors and the hardware monitors sit on it. The
+ * controller runs SMBus transactions itself and cannot send arbitrary
+ * I2C messages, so the adapter only offers SMBus operations (see
+ * lib/orion_i2c).
+ *
+ * The controller is PCI function 00:1f.3 on every chipset that has it,
+ * its registers in I/O space behind BAR 4. Completion is polled rather
+ * than interrupt driven: a transaction takes under a millisecond at
+ * 100 kHz and the controller's interrupt is often routed to the SMI
+ * handler of the firmware. At start the driver looks for SPD EEPROMs
+ * at 0x50-0x57, as their presence follows from the memory slots; other
+ * devices are added by the firmware enumeration with ADD_DEVICE. The
+ * bus is served on the driver's own IPC endpoint.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_driver::{DriverError, DriverResult};
+use orion_i2c::control::{I2C_CTRL_ADD_DEVICE, I2C_CTRL_SMBUS, I2C_CTRL_TRANSFER};
+use orion_i2c::{
+    handle_control, BoardDevice, Functionality, I2cAdapter, I2cBus, I2cError, SmbusData, SmbusOp, I2C_DEVICE_DRIVERS,
+    SMBUS_BLOCK_MAX,
+};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::rights::CAP_ADMIN;
+use orion_sys::nanosleep;
+
+// Global allocator for the driver
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the bus and its devices
+const HEAP_SIZE: usize = 256 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+// PCI configuration mechanism #1
+const PCI_CONFIG_ADDRESS: u16 = 0xCF8;
+const PCI_CONFIG_DATA: u16 = 0xCFC;
+
+/// Bus, device and function of the SMBus controller
+const I801_PCI_LOCATION: (u8, u8, u8) = (0, 0x1F, 3);
+
+const PCI_VENDOR_INTEL: u16 = 0x8086;
+
+/// SMBus controllers of the chipsets the driver knows
+const I801_DEVICE_IDS: &[u16] = &[
+    0x2930, // ICH9 (QEMU q35)
+    0x3A30, // ICH10
+    0x1C22, // 6 Series
+    0x1E22, // 7 Series
+    0x8C22, // 8 Series
+    0x9C22, // 8 Series LP
+    0xA123, // Sunrise Point-H
+    0x9D23, // Sunrise Point-LP
+    0xA323, // Cannon Lake-H
+    0x9DA3, // Cannon Lake-LP
+    0x06A3, // Comet Lake
+    0xA0A3, // Tiger Lake-LP
+    0x43A3, // Tiger Lake-H
+    0x7AA3, // Alder Lake-S
+    0x51A3, // Alder Lake-P
+    0x7A23, // Raptor Lake-S
+];
+
+// PCI configuration registers
+const PCI_ID: u8 = 0x00;
+const PCI_COMMAND: u8 = 0x04;
+const PCI_BAR4: u8 = 0x20;
+const SMBUS_HOSTC: u8 = 0x40;
+
+const PCI_COMMAND_IO: u32 = 0x0001;
+const PCI_BAR_IO_MASK: u32 = 0xFFFC;
+
+// HOSTC: host enabled, SMI routing and I2C mode off
+const HOSTC_HST_EN: u32 = 0x01;
+const HOSTC_SMB_SMI_EN: u32 = 0x02;
+const HOSTC_I2C_EN: u32 = 0x04;
+
+// Host registers (relative to BAR 4)
+const SMBHSTSTS: u16 = 0x00;
+const SMBHSTCNT: u16 = 0x02;
+const SMBHSTCMD: u16 = 0x03;
+const SMBXMITADD: u16 = 0x04;
+const SMBHSTDAT0: u16 = 0x05;
+const SMBHSTDAT1: u16 = 0x06;
+const SMBBLKDAT: u16 = 0x07;
+const SMBAUXCTL: u16 = 0x0D;
+
+// SMBHSTSTS bits, cleared by writing them
+const STS_HOST_BUSY: u8 = 0x01;
+const STS_INTR: u8 = 0x02;
+const STS_DEV_ERR: u8 = 0x04;
+const STS_BUS_ERR: u8 = 0x08;
+const STS_FAILED: u8 = 0x10;
+const STS_BYTE_DONE: u8 = 0x80;
+const STS_CLEAR: u8 = STS_INTR | STS_DEV_ERR | STS_BUS_ERR | STS_FAILED | STS_BYTE_DONE;
+const STS_ERRORS: u8 = STS_DEV_ERR | STS_BUS_ERR | STS_FAILED;
+
+// SMBHSTCNT: the protocol in bits 2-4, kill and start
+const CNT_KILL: u8 = 0x02;
+const CNT_QUICK: u8 = 0x00;
+const CNT_BYTE: u8 = 0x04;
+const CNT_BYTE_DATA: u8 = 0x08;
+const CNT_WORD_DATA: u8 = 0x0C;
+const CNT_BLOCK_DATA: u8 = 0x14;
+const CNT_START: u8 = 0x40;
+
+/// SMBAUXCTL: the block data register is a 32-byte buffer
+const AUXCTL_E32B: u8 = 0x02;
+
+/// Read bit of SMBXMITADD
+const XMIT_READ: u8 = 0x01;
+
+/// Checks of the status while a transaction runs, SMBUS_POLL_NS apart;
+/// the SMBus timeout is 25-35 ms
+const SMBUS_POLL_NS: u64 = 10_000;
+const SMBUS_MAX_POLLS: u32 = 3_500;
+
+/// Where the SPD EEPROMs of the memory modules answer
+const SPD_ADDRESSES: core::ops::RangeInclusive<u16> = 0x50..=0x57;
+const SPD_COMPATIBLE: &str = "atmel,24c02";
+
+// ========================================
+// HARDWARE ACCESS
+// ========================================
+
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    core::arch::asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn outb(port: u16, value: u8) {
+    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+unsafe fn inl(port: u16) -> u32 {
+    let value: u32;
+    core::arch::asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn outl(port: u16, value: u32) {
+    core::arch::asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags));
+}
+
+fn pci_config_address((bus, device, function): (u8, u8, u8), offset: u8) -> u32 {
+    0x8000_0000 | (bus as u32) << 16 | (device as u32) << 11 | (function as u32) << 8 | (offset & 0xFC) as u32
+}
+
+fn pci_config_read(location: (u8, u8, u8), offset: u8) -> u32 {
+    unsafe {
+        outl(PCI_CONFIG_ADDRESS, pci_config_address(location, offset));
+        inl(PCI_CONFIG_DATA)
+    }
+}
+
+fn pci_config_write(location: (u8, u8, u8), offset: u8, value: u32) {
+    unsafe {
+        outl(PCI_CONFIG_ADDRESS, pci_config_address(location, offset));
+        outl(PCI_CONFIG_DATA, value);
+    }
+}
+
+/// Find the controller and turn it on; the base of its registers
+fn enable_controller() -> DriverResult<u16> {
+    let id = pci_config_read(I801_PCI_LOCATION, PCI_ID);
+    let (vendor, device) = (id as u16, (id >> 16) as u16);
+    if vendor != PCI_VENDOR_INTEL || !I801_DEVICE_IDS.contains(&device) {
+        return Err(DriverError::DeviceNotFound);
+    }
+    let base = (pci_config_read(I801_PCI_LOCATION, PCI_BAR4) & PCI_BAR_IO_MASK) as u16;
+    if base == 0 {
+        return Err(DriverError::DeviceNotFound);
+    }
+    let command = pci_config_read(I801_PCI_LOCATION, PCI_COMMAND);
+    pci_config_write(I801_PCI_LOCATION, PCI_COMMAND, (command & 0xFFFF) | PCI_COMMAND_IO);
+    let hostc = pci_config_read(I801_PCI_LOCATION, SMBUS_HOSTC);
+    pci_config_write(I801_PCI_LOCATION, SMBUS_HOSTC, (hostc & !(HOSTC_SMB_SMI_EN | HOSTC_I2C_EN)) | HOSTC_HST_EN);
+    Ok(base)
+}
+
+/// Register access, abstracted so the driver runs against a simulated
+/// controller in the tests
+pub trait SmbusRegisters {
+    fn read(&mut self, register: u16) -> u8;
+    fn write(&mut self, register: u16, value: u8);
+}
+
+pub struct PortIo {
+    base: u16,
+}
+
+impl SmbusRegisters for PortIo {
+    fn read(&mut self, register: u16) -> u8 {
+        unsafe { inb(self.base + register) }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        unsafe { outb(self.base + register, value) }
+    }
+}
+
+// ========================================
+// ADAPTER
+// ========================================
+
+pub struct I801<R: SmbusRegisters> {
+    registers: R,
+    /// Whether block transfers go through the 32-byte buffer; without
+    /// it the driver does not offer them
+    block_buffer: bool,
+}
+
+impl<R: SmbusRegisters> I801<R> {
+    pub fn new(mut registers: R) -> Self {
+        registers.write(SMBHSTSTS, STS_CLEAR);
+        registers.write(SMBAUXCTL, AUXCTL_E32B);
+        let block_buffer = registers.read(SMBAUXCTL) & AUXCTL_E32B != 0;
+        Self { registers, block_buffer }
+    }
+
+    /// Wait for the running transaction to end
+    fn wait(&mut self) -> Result<(), I2cError> {
+        for _ in 0..SMBUS_MAX_POLLS {
+            let status = self.registers.read(SMBHSTSTS);
+            if status & STS_HOST_BUSY == 0 && status & (STS_INTR | STS_ERRORS) != 0 {
+                self.registers.write(SMBHSTSTS, status & STS_CLEAR);
+                return if status & STS_DEV_ERR != 0 {
+                    Err(I2cError::NoAck)
+                } else if status & STS_BUS_ERR != 0 {
+                    Err(I2cError::ArbitrationLost)
+                } else if status & STS_FAILED != 0 {
+                    Err(I2cError::BusError)
+                } else {
+                    Ok(())
+                };
+            }
+            let _ = nanosleep(SMBUS_POLL_NS);
+        }
+        // Stop the transaction so the next one can start
+        self.registers.write(SMBHSTCNT, CNT_KILL);
+        self.registers.write(SMBHSTCNT, 0);
+        self.registers.write(SMBHSTSTS, STS_CLEAR);
+        Err(I2cError::Timeout)
+    }
+
+    /// Run one transaction of `protocol` with `address`
+    fn execute(&mut self, address: u16, read: bool, protocol: u8, command: u8) -> Result<(), I2cError> {
+        if self.registers.read(SMBHSTSTS) & STS_HOST_BUSY != 0 {
+            return Err(I2cError::BusError);
+        }
+        self.registers.write(SMBHSTSTS, STS_CLEAR);
+        self.registers.write(SMBXMITADD, (address as u8) << 1 | if read { XMIT_READ } else { 0 });
+        self.registers.write(SMBHSTCMD, command);
+        self.registers.write(SMBHSTCNT, protocol | CNT_START);
+        self.wait()
+    }
+
+    /// Reading the control register rewinds the block buffer
+    fn rewind_block(&mut self) {
+        let _ = self.registers.read(SMBHSTCNT);
+    }
+}
+
+impl<R: SmbusRegisters> I2cAdapter for I801<R> {
+    fn functionality(&self) -> Functionality {
+        let functionality = Functionality::SMBUS_QUICK
+            | Functionality::SMBUS_BYTE
+            | Functionality::SMBUS_BYTE_DATA
+            | Functionality::SMBUS_WORD_DATA;
+        if self.block_buffer {
+            functionality | Functionality::SMBUS_BLOCK_DATA
+        } else {
+            functionality
+        }
+    }
+
+    fn smbus(&mut self, address: u16, op: &SmbusOp) -> Result<SmbusData, I2cError> {
+        match op {
+            SmbusOp::Quick { read } => self.execute(address, *read, CNT_QUICK, 0).map(|_| SmbusData::None),
+            SmbusOp::ReadByte => {
+                self.execute(address, true, CNT_BYTE, 0)?;
+                Ok(SmbusData::Byte(self.registers.read(SMBHSTDAT0)))
+            }
+            // The byte goes out in the command slot
+            SmbusOp::WriteByte(value) => self.execute(address, false, CNT_BYTE, *value).map(|_| SmbusData::None),
+            SmbusOp::ReadByteData(command) => {
+                self.execute(address, true, CNT_BYTE_DATA, *command)?;
+                Ok(SmbusData::Byte(self.registers.read(SMBHSTDAT0)))
+            }
+            SmbusOp::WriteByteData(command, value) => {
+                self.registers.write(SMBHSTDAT0, *value);
+                self.execute(address, false, CNT_BYTE_DATA, *command).map(|_| SmbusData::None)
+            }
+            SmbusOp::ReadWordData(command) => {
+                self.execute(address, true, CNT_WORD_DATA, *command)?;
+                let low = self.registers.read(SMBHSTDAT0);
+                let high = self.registers.read(SMBHSTDAT1);
+                Ok(SmbusData::Word(u16::from_le_bytes([low, high])))
+            }
+            SmbusOp::WriteWordData(command, value) => {
+                let [low, high] = value.to_le_bytes();
+                self.registers.write(SMBHSTDAT0, low);
+                self.registers.write(SMBHSTDAT1, high);
+                self.execute(address, false, CNT_WORD_DATA, *command).map(|_| SmbusData::None)
+            }
+            SmbusOp::ReadBlockData(command) => {
+                self.execute(address, true, CNT_BLOCK_DATA, *command)?;
+                let count = self.registers.read(SMBHSTDAT0) as usize;
+                if count == 0 || count > SMBUS_BLOCK_MAX {
+                    return Err(I2cError::BusError);
+                }
+                self.rewind_block();
+                Ok(SmbusData::Block((0..count).map(|_| self.registers.read(SMBBLKDAT)).collect()))
+            }
+            SmbusOp::WriteBlockData(command, data) => {
+                self.registers.write(SMBHSTDAT0, data.len() as u8);
+                self.rewind_block();
+                for &byte in data {
+                    self.registers.write(SMBBLKDAT, byte);
+                }
+                self.execute(address, false, CNT_BLOCK_DATA, *command).map(|_| SmbusData::None)
+            }
+        }
+    }
+}
+
+/// Add the SPD EEPROMs that answer to the bus
+fn scan_spd<A: I2cAdapter>(bus: &mut I2cBus<A>) -> usize {
+    let mut found = 0;
+    for address in SPD_ADDRESSES {
+        if bus.smbus(address, &SmbusOp::ReadByte).is_err() {
+            continue;
+        }
+        let device = BoardDevice { address, compatible: vec![String::from(SPD_COMPATIBLE)], ..Default::default() };
+        if bus.add_device(device).is_ok() {
+            found += 1;
+        }
+    }
+    found
+}
+
+// ========================================
+// SMBUS SERVICE
+// ========================================
+
+struct SmbusServer {
+    bus: I2cBus<I801<PortIo>>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl SmbusServer {
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        // Listing the bus is open; reaching the devices is not
+        let privileged = matches!(
+            message.data.get(..4).map(|opcode| u32::from_le_bytes([opcode[0], opcode[1], opcode[2], opcode[3]])),
+            Some(I2C_CTRL_TRANSFER | I2C_CTRL_SMBUS | I2C_CTRL_ADD_DEVICE)
+        );
+        let admin = privileged && self.capabilities.check_rights(message.capability, CAP_ADMIN, message.sender);
+        let response = handle_control(&mut self.bus, &message.data, admin);
+        self.ipc_channel.send(message.sender, &response);
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let Ok(base) = enable_controller() else {
+        return;
+    };
+    let mut bus = I2cBus::new(I801::new(PortIo { base }), Vec::new(), I2C_DEVICE_DRIVERS);
+    scan_spd(&mut bus);
+    let mut server = SmbusServer { bus, ipc_channel: IpcChannel::new(), capabilities: Capability::new() };
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    /// A controller running its transactions at once against the
+    /// registers of the devices in `devices`
+    #[derive(Default)]
+    struct FakeSmbus {
+        registers: [u8; 16],
+        block: Vec<u8>,
+        block_index: usize,
+        devices: BTreeMap<u8, [u8; 256]>,
+    }
+
+    impl FakeSmbus {
+        fn run(&mut self, control: u8) {
+            let address = self.registers[SMBXMITADD as usize] >> 1;
+            let read = self.registers[SMBXMITADD as usize] & XMIT_READ != 0;
+            let command = self.registers[SMBHSTCMD as usize] as usize;
+            let Some(memory) = self.devices.get_mut(&address) else {
+                self.registers[SMBHSTSTS as usize] = STS_DEV_ERR;
+                return;
+            };
+            let data = &mut self.registers[SMBHSTDAT0 as usize..=SMBHSTDAT1 as usize];
+            match (control & 0x1C, read) {
+                (CNT_BYTE, true) => data[0] = memory[0],
+                (CNT_BYTE_DATA, true) => data[0] = memory[command],
+                (CNT_BYTE_DATA, false) => memory[command] = data[0],
+                (CNT_WORD_DATA, true) => data.copy_from_slice(&memory[command..command + 2]),
+                (CNT_WORD_DATA, false) => memory[command..command + 2].copy_from_slice(data),
+                (CNT_BLOCK_DATA, true) => {
+                    let count = memory[command] as usize;
+                    data[0] = count as u8;
+                    self.block = memory[command + 1..command + 1 + count].to_vec();
+                }
+                (CNT_BLOCK_DATA, false) => {
+                    memory[command] = self.block.len() as u8;
+                    memory[command + 1..command + 1 + self.block.len()].copy_from_slice(&self.block);
+                }
+                _ => {}
+            }
+            self.registers[SMBHSTSTS as usize] = STS_INTR;
+        }
+    }
+
+    impl SmbusRegisters for FakeSmbus {
+        fn read(&mut self, register: u16) -> u8 {
+            match register {
+                SMBHSTCNT => {
+                    self.block_index = 0;
+                    self.registers[register as usize]
+                }
+                SMBBLKDAT => {
+                    self.block_index += 1;
+                    self.block.get(self.block_index - 1).copied().unwrap_or(0)
+                }
+                _ => self.registers[register as usize],
+            }
+        }
+
+        fn write(&mut self, register: u16, value: u8) {
+            match register {
+                SMBHSTSTS => self.registers[register as usize] &= !value,
+                SMBHSTCNT if value & CNT_START != 0 => self.run(value),
+                SMBBLKDAT => {
+                    self.block.truncate(self.block_index);
+                    self.block.push(value);
+                    self.block_index += 1;
+                }
+                _ => self.registers[register as usize] = value,
+            }
+        }
+    }
+
+    fn controller() -> I801<FakeSmbus> {
+        let mut registers = FakeSmbus::default();
+        let mut spd = [0u8; 256];
+        spd[..4].copy_from_slice(&[0x92, 0x10, 0x0B, 0x02]);
+        registers.devices.insert(0x50, spd);
+        registers.devices.insert(0x52, [0; 256]);
+        I801::new(registers)
+    }
+
+    #[test]
+    fn test_runs_smbus_transactions() {
+        let mut adapter = controller();
+        assert!(adapter.functionality().contains(Functionality::SMBUS_BLOCK_DATA));
+        assert_eq!(adapter.smbus(0x50, &SmbusOp::ReadByteData(2)), Ok(SmbusData::Byte(0x0B)));
+        adapter.smbus(0x52, &SmbusOp::WriteWordData(0x10, 0xBEEF)).unwrap();
+        assert_eq!(adapter.smbus(0x52, &SmbusOp::ReadWordData(0x10)), Ok(SmbusData::Word(0xBEEF)));
+        adapter.smbus(0x52, &SmbusOp::WriteBlockData(0x20, vec![1, 2, 3])).unwrap();
+        assert_eq!(adapter.smbus(0x52, &SmbusOp::ReadBlockData(0x20)), Ok(SmbusData::Block(vec![1, 2, 3])));
+        assert_eq!(adapter.smbus(0x30, &SmbusOp::ReadByte), Err(I2cError::NoAck));
+        // Plain I2C messages are not something the controller sends
+        assert_eq!(adapter.transfer(&mut []), Err(I2cError::NotSupported));
+    }
+
+    #[test]
+    fn test_finds_the_spd_eeproms() {
+        let mut bus = I2cBus::new(controller(), Vec::new(), I2C_DEVICE_DRIVERS);
+        assert_eq!(scan_spd(&mut bus), 2);
+        let found: Vec<_> = bus.devices().map(|(device, driver)| (device.address, driver)).collect();
+        assert_eq!(found, [(0x50, Some("at24")), (0x52, Some("at24"))]);
+    }
+}
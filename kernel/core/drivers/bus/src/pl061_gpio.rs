@@ -0,0 +1,369 @@
+/*
+ * Orion Operating System - ARM PL061 GPIO Driver
+ *
+ * Driver for the ARM PrimeCell PL061, the GPIO controller of QEMU's virt
+ * machine and of many ARM boards: eight lines, each an input or an
+ * output, whose inputs interrupt on either edge, both, or a level. The
+ * chip implements GpioChip and a GpioController serves its lines on the
+ * driver's own IPC endpoint (see lib/orion_gpio).
+ *
+ * Interrupts are not delivered to platform drivers, so while a line is
+ * requested with a trigger the driver checks the masked interrupt status
+ * every GPIO_POLL_NS. The controller latches edges until they are
+ * cleared, so none is missed between two checks; edges closer together
+ * than that are reported once.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use orion_cap::Capability;
+use orion_driver::{DriverError, DriverResult, MmioAccessor, MmioPermissions};
+use orion_gpio::control::GPIO_CTRL_REQUEST;
+use orion_gpio::{GpioChip, GpioController, GpioError, Replies, Trigger};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::rights::CAP_ADMIN;
+use orion_sys::{clock_get, nanosleep};
+
+// Global allocator for the driver
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the line owners and their event queues
+const HEAP_SIZE: usize = 256 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+/// The PL061 of QEMU's virt machine
+const PL061_BASE_ADDRESS: u64 = 0x0903_0000;
+const PL061_WINDOW_SIZE: usize = 0x1000;
+
+const PL061_LINES: u32 = 8;
+
+// Registers. GPIODATA is seen through an address mask: bits 2-9 of the
+// offset select the lines a read returns and a write changes.
+const GPIODATA: u64 = 0x000;
+const GPIODIR: u64 = 0x400;
+const GPIOIS: u64 = 0x404;
+const GPIOIBE: u64 = 0x408;
+const GPIOIEV: u64 = 0x40C;
+const GPIOIE: u64 = 0x410;
+const GPIOMIS: u64 = 0x418;
+const GPIOIC: u64 = 0x41C;
+const GPIOPERIPHID0: u64 = 0xFE0;
+
+/// Peripheral id of the PL061, bytes 0-2 less the revision
+const PL061_PERIPH_ID: [u32; 3] = [0x61, 0x10, 0x04];
+
+/// How often the interrupt status is checked while a line has a trigger
+const GPIO_POLL_NS: u64 = 1_000_000;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// Register access, abstracted so the driver runs against a simulated
+/// controller in the tests
+pub trait Pl061Registers {
+    fn read(&mut self, register: u64) -> u32;
+    fn write(&mut self, register: u64, value: u32);
+}
+
+impl Pl061Registers for MmioAccessor {
+    fn read(&mut self, register: u64) -> u32 {
+        self.read_u32(register).unwrap_or(0)
+    }
+
+    fn write(&mut self, register: u64, value: u32) {
+        let _ = self.write_u32(register, value);
+    }
+}
+
+// ========================================
+// CHIP
+// ========================================
+
+pub struct Pl061<R: Pl061Registers> {
+    registers: R,
+}
+
+impl<R: Pl061Registers> Pl061<R> {
+    /// Check the controller is a PL061 and quiet its interrupts
+    pub fn probe(mut registers: R) -> DriverResult<Self> {
+        for (index, expected) in PL061_PERIPH_ID.iter().enumerate() {
+            let id = registers.read(GPIOPERIPHID0 + 4 * index as u64);
+            let id = if index == 2 { id & 0x0F } else { id & 0xFF };
+            if id != *expected {
+                return Err(DriverError::DeviceNotFound);
+            }
+        }
+        registers.write(GPIOIE, 0);
+        registers.write(GPIOIC, 0xFF);
+        Ok(Self { registers })
+    }
+
+    fn check(line: u32) -> Result<u32, GpioError> {
+        if line < PL061_LINES {
+            Ok(1 << line)
+        } else {
+            Err(GpioError::InvalidLine)
+        }
+    }
+
+    /// Set the bits of `mask` in `register` to `value`
+    fn update(&mut self, register: u64, mask: u32, value: bool) {
+        let current = self.registers.read(register);
+        self.registers.write(register, if value { current | mask } else { current & !mask });
+    }
+}
+
+impl<R: Pl061Registers> GpioChip for Pl061<R> {
+    fn label(&self) -> &str {
+        "pl061"
+    }
+
+    fn lines(&self) -> u32 {
+        PL061_LINES
+    }
+
+    fn direction_input(&mut self, line: u32) -> Result<(), GpioError> {
+        let mask = Self::check(line)?;
+        self.update(GPIODIR, mask, false);
+        Ok(())
+    }
+
+    fn direction_output(&mut self, line: u32, value: bool) -> Result<(), GpioError> {
+        let mask = Self::check(line)?;
+        // Set the level first so the line does not glitch
+        self.set(line, value)?;
+        self.update(GPIODIR, mask, true);
+        Ok(())
+    }
+
+    fn get(&mut self, line: u32) -> Result<bool, GpioError> {
+        let mask = Self::check(line)?;
+        Ok(self.registers.read(GPIODATA + ((mask as u64) << 2)) & mask != 0)
+    }
+
+    fn set(&mut self, line: u32, value: bool) -> Result<(), GpioError> {
+        let mask = Self::check(line)?;
+        self.registers.write(GPIODATA + ((mask as u64) << 2), if value { mask } else { 0 });
+        Ok(())
+    }
+
+    fn set_trigger(&mut self, line: u32, trigger: Trigger) -> Result<(), GpioError> {
+        let mask = Self::check(line)?;
+        self.update(GPIOIE, mask, false);
+        if trigger == Trigger::None {
+            return Ok(());
+        }
+        self.update(GPIOIS, mask, trigger.is_level());
+        self.update(GPIOIBE, mask, trigger == Trigger::Both);
+        self.update(GPIOIEV, mask, matches!(trigger, Trigger::Rising | Trigger::High));
+        // An edge latched under the old configuration is not reported
+        self.registers.write(GPIOIC, mask);
+        self.update(GPIOIE, mask, true);
+        Ok(())
+    }
+
+    fn pending(&mut self) -> u32 {
+        self.registers.read(GPIOMIS) & 0xFF
+    }
+
+    fn ack(&mut self, mask: u32) {
+        self.registers.write(GPIOIC, mask & 0xFF);
+    }
+}
+
+// ========================================
+// GPIO SERVICE
+// ========================================
+
+struct GpioServer {
+    controller: GpioController<Pl061<MmioAccessor>>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+/// Whether a requested line has a trigger, so interrupts are to be checked
+fn watching<R: Pl061Registers>(controller: &GpioController<Pl061<R>>) -> bool {
+    let lines = controller.lines();
+    (0..lines.count()).any(|line| lines.info(line).is_some_and(|info| info.trigger != Trigger::None))
+}
+
+impl GpioServer {
+    fn send(&self, replies: Replies) {
+        for (receiver, response) in replies {
+            self.ipc_channel.send(receiver, &response);
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            let replies = self.controller.interrupt(monotonic_ns());
+            self.send(replies);
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None if watching(&self.controller) => {
+                    let _ = nanosleep(GPIO_POLL_NS);
+                }
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        // Taking a line needs the administrative right; using it needs
+        // to be its owner
+        let requesting = message.data.get(..4) == Some(&GPIO_CTRL_REQUEST.to_le_bytes()[..]);
+        let admin = requesting && self.capabilities.check_rights(message.capability, CAP_ADMIN, message.sender);
+        let replies = self.controller.handle(message.sender, &message.data, admin);
+        self.send(replies);
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mmio = unsafe {
+        MmioAccessor::new(
+            PL061_BASE_ADDRESS,
+            PL061_WINDOW_SIZE,
+            MmioPermissions::READ | MmioPermissions::WRITE | MmioPermissions::UNCACHED,
+        )
+    };
+    let Ok(chip) = Pl061::probe(mmio) else {
+        return;
+    };
+    let mut server = GpioServer {
+        controller: GpioController::new(chip),
+        ipc_channel: IpcChannel::new(),
+        capabilities: Capability::new(),
+    };
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use orion_gpio::control::{GPIO_CTRL_EVENTS, GPIO_CTRL_SET};
+    use orion_gpio::LineConfig;
+
+    /// A PL061 whose input levels the test drives
+    #[derive(Default)]
+    struct FakePl061 {
+        data: u32,
+        dir: u32,
+        is: u32,
+        ibe: u32,
+        iev: u32,
+        ie: u32,
+        ris: u32,
+    }
+
+    impl FakePl061 {
+        fn drive(&mut self, line: u32, level: bool) {
+            let mask = 1 << line;
+            let before = self.data & mask != 0;
+            self.data = if level { self.data | mask } else { self.data & !mask };
+            let fires = if self.is & mask != 0 {
+                level == (self.iev & mask != 0)
+            } else if self.ibe & mask != 0 {
+                before != level
+            } else {
+                before != level && level == (self.iev & mask != 0)
+            };
+            if fires {
+                self.ris |= mask;
+            }
+        }
+    }
+
+    impl Pl061Registers for FakePl061 {
+        fn read(&mut self, register: u64) -> u32 {
+            match register {
+                0x000..=0x3FC => self.data & (register >> 2) as u32,
+                GPIODIR => self.dir,
+                GPIOIS => self.is,
+                GPIOIBE => self.ibe,
+                GPIOIEV => self.iev,
+                GPIOIE => self.ie,
+                GPIOMIS => self.ris & self.ie,
+                0xFE0..=0xFE8 => PL061_PERIPH_ID[((register - GPIOPERIPHID0) / 4) as usize],
+                _ => 0,
+            }
+        }
+
+        fn write(&mut self, register: u64, value: u32) {
+            match register {
+                0x000..=0x3FC => {
+                    let mask = (register >> 2) as u32 & self.dir;
+                    self.data = (self.data & !mask) | (value & mask);
+                }
+                GPIODIR => self.dir = value,
+                GPIOIS => self.is = value,
+                GPIOIBE => self.ibe = value,
+                GPIOIEV => self.iev = value,
+                GPIOIE => self.ie = value,
+                GPIOIC => self.ris &= !value,
+                _ => {}
+            }
+        }
+    }
+
+    fn line_request(opcode: u32, line: u32, argument: u32) -> Vec<u8> {
+        [opcode, line, argument].iter().flat_map(|field| field.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_drives_and_watches_lines() {
+        let mut controller = GpioController::new(Pl061::probe(FakePl061::default()).unwrap());
+        controller.lines_mut().request(1, 3, "led", LineConfig::output(false)).unwrap();
+        controller.handle(1, &line_request(GPIO_CTRL_SET, 3, 1), false);
+        assert_eq!(controller.lines().chip().registers.data, 1 << 3);
+        assert!(!watching(&controller));
+
+        // An active-low button falls when pressed
+        controller.lines_mut().request(1, 5, "button", LineConfig::input(Trigger::Rising).active_low()).unwrap();
+        assert!(watching(&controller));
+        controller.lines_mut().chip_mut().registers.drive(5, true);
+        assert!(controller.handle(1, &line_request(GPIO_CTRL_EVENTS, 5, 1), false).is_empty());
+        controller.lines_mut().chip_mut().registers.drive(5, false);
+        let replies = controller.interrupt(7);
+        assert_eq!(replies.len(), 1);
+        assert_eq!(&replies[0].1[..8], &[0, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(controller.lines().chip().registers.ris, 0);
+
+        // Writes through the data mask leave the other lines alone
+        let chip = controller.lines_mut().chip_mut();
+        chip.set(5, true).unwrap();
+        assert_eq!(chip.get(3), Ok(true));
+        assert_eq!(chip.set_trigger(9, Trigger::Both), Err(GpioError::InvalidLine));
+    }
+}
@@ -0,0 +1,496 @@
+/*
+ * Orion Operating System - ACPI Battery and AC Adapter Driver
+ *
+ * Driver for laptop batteries and AC adapters behind the ACPI embedded
+ * controller. The EC exposes an SMBus host controller (ACPI 12.9) through
+ * which the Smart Battery (ACPI0002, SMBus address 0x0B) and the Smart
+ * Battery Charger (address 0x09) are read directly, without evaluating
+ * AML: the battery reports its charge, rate and status, the charger
+ * whether the AC adapter is plugged in.
+ *
+ * Like the TPM, the battery is a platform device described by ACPI rather
+ * than a PCI function, so the driver serves its own "acpi-battery" IPC
+ * endpoint. Readings are open to everyone; the power policy service polls
+ * them (services/power). Each fresh reading also forwards the battery
+ * temperature to the thermal server as the "battery" zone.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use orion_driver::{DriverError, DriverResult};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_sys::clock_get;
+use orion_thermal::{decikelvin_to_mc, ThermalSensor};
+
+// Global allocator for the driver
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+// Embedded controller ports (ECDT defaults on PC platforms)
+const EC_DATA_PORT: u16 = 0x62;
+const EC_COMMAND_PORT: u16 = 0x66;
+
+// EC status register
+const EC_STATUS_OBF: u8 = 0x01;
+const EC_STATUS_IBF: u8 = 0x02;
+
+// EC commands
+const EC_READ: u8 = 0x80;
+const EC_WRITE: u8 = 0x81;
+
+/// Offset of the SMBus host controller in EC space, given by the _EC
+/// object of the ACPI0001 device
+const SMB_HC_BASE: u8 = 0x18;
+
+// SMBus host controller registers (relative to SMB_HC_BASE)
+const SMB_PRTCL: u8 = 0x00;
+const SMB_STS: u8 = 0x01;
+const SMB_ADDR: u8 = 0x02;
+const SMB_CMD: u8 = 0x03;
+const SMB_DATA: u8 = 0x04;
+
+const SMB_PRTCL_READ_WORD: u8 = 0x09;
+const SMB_STS_DONE: u8 = 0x80;
+const SMB_STS_STATUS_MASK: u8 = 0x1F;
+
+// SMBus addresses
+const SBS_BATTERY_ADDRESS: u8 = 0x0B;
+const SBS_CHARGER_ADDRESS: u8 = 0x09;
+
+// Smart Battery Data commands
+const SBS_BATTERY_MODE: u8 = 0x03;
+const SBS_TEMPERATURE: u8 = 0x08;
+const SBS_VOLTAGE: u8 = 0x09;
+const SBS_CURRENT: u8 = 0x0A;
+const SBS_RELATIVE_STATE_OF_CHARGE: u8 = 0x0D;
+const SBS_REMAINING_CAPACITY: u8 = 0x0F;
+const SBS_FULL_CHARGE_CAPACITY: u8 = 0x10;
+const SBS_RUN_TIME_TO_EMPTY: u8 = 0x11;
+const SBS_AVERAGE_TIME_TO_FULL: u8 = 0x13;
+const SBS_BATTERY_STATUS: u8 = 0x16;
+const SBS_DESIGN_CAPACITY: u8 = 0x18;
+
+// Smart Battery Charger commands
+const SBC_CHARGER_STATUS: u8 = 0x13;
+
+// BatteryMode: capacities in 10 mWh instead of mAh
+const BATTERY_MODE_CAPACITY_MODE: u16 = 1 << 15;
+
+// BatteryStatus bits
+const BATTERY_STATUS_REMAINING_CAPACITY_ALARM: u16 = 1 << 9;
+const BATTERY_STATUS_TERMINATE_DISCHARGE_ALARM: u16 = 1 << 11;
+const BATTERY_STATUS_FULLY_DISCHARGED: u16 = 1 << 4;
+const BATTERY_STATUS_FULLY_CHARGED: u16 = 1 << 5;
+const BATTERY_STATUS_DISCHARGING: u16 = 1 << 6;
+
+// ChargerStatus bits
+const CHARGER_STATUS_BATTERY_PRESENT: u16 = 1 << 14;
+const CHARGER_STATUS_AC_PRESENT: u16 = 1 << 15;
+
+/// Times reported as 65535 minutes mean "not applicable"
+const SBS_TIME_UNKNOWN: u16 = 0xFFFF;
+
+/// Polls of the EC status register before a transaction is abandoned
+const EC_TIMEOUT_SPINS: u32 = 100_000;
+
+/// Readings are cached this long since every one costs several SMBus
+/// transactions
+const READING_MAX_AGE_NS: u64 = 1_000_000_000;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+// Trips of the battery zone: lithium cells should not be discharged much
+// above 60 C
+const BATTERY_PASSIVE_MC: i32 = 50_000;
+const BATTERY_CRITICAL_MC: i32 = 60_000;
+
+// ========================================
+// EMBEDDED CONTROLLER
+// ========================================
+
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    core::arch::asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn outb(port: u16, value: u8) {
+    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+/// Word reads from SMBus devices, abstracted so readings can be decoded
+/// from canned values in the tests
+pub trait SmbusWord {
+    fn read_word(&mut self, address: u8, command: u8) -> DriverResult<u16>;
+}
+
+pub struct EmbeddedController;
+
+impl EmbeddedController {
+    fn wait_status(&self, mask: u8, set: bool) -> DriverResult<()> {
+        for _ in 0..EC_TIMEOUT_SPINS {
+            let status = unsafe { inb(EC_COMMAND_PORT) };
+            if (status & mask != 0) == set {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+
+    fn read(&self, offset: u8) -> DriverResult<u8> {
+        self.wait_status(EC_STATUS_IBF, false)?;
+        unsafe { outb(EC_COMMAND_PORT, EC_READ) };
+        self.wait_status(EC_STATUS_IBF, false)?;
+        unsafe { outb(EC_DATA_PORT, offset) };
+        self.wait_status(EC_STATUS_OBF, true)?;
+        Ok(unsafe { inb(EC_DATA_PORT) })
+    }
+
+    fn write(&self, offset: u8, value: u8) -> DriverResult<()> {
+        self.wait_status(EC_STATUS_IBF, false)?;
+        unsafe { outb(EC_COMMAND_PORT, EC_WRITE) };
+        self.wait_status(EC_STATUS_IBF, false)?;
+        unsafe { outb(EC_DATA_PORT, offset) };
+        self.wait_status(EC_STATUS_IBF, false)?;
+        unsafe { outb(EC_DATA_PORT, value) };
+        Ok(())
+    }
+}
+
+impl SmbusWord for EmbeddedController {
+    fn read_word(&mut self, address: u8, command: u8) -> DriverResult<u16> {
+        self.write(SMB_HC_BASE + SMB_ADDR, address << 1)?;
+        self.write(SMB_HC_BASE + SMB_CMD, command)?;
+        // Writing the protocol starts the transaction; it reads back as
+        // zero once the host controller is done
+        self.write(SMB_HC_BASE + SMB_PRTCL, SMB_PRTCL_READ_WORD)?;
+        for _ in 0..EC_TIMEOUT_SPINS {
+            if self.read(SMB_HC_BASE + SMB_PRTCL)? == 0 {
+                let status = self.read(SMB_HC_BASE + SMB_STS)?;
+                if status & SMB_STS_DONE == 0 || status & SMB_STS_STATUS_MASK != 0 {
+                    return Err(DriverError::IoError);
+                }
+                let low = self.read(SMB_HC_BASE + SMB_DATA)?;
+                let high = self.read(SMB_HC_BASE + SMB_DATA + 1)?;
+                return Ok(u16::from_le_bytes([low, high]));
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+}
+
+// ========================================
+// BATTERY READINGS
+// ========================================
+
+// Reading state flags
+pub const STATE_DISCHARGING: u32 = 1 << 0;
+pub const STATE_CHARGING: u32 = 1 << 1;
+pub const STATE_CRITICAL: u32 = 1 << 2;
+pub const STATE_FULL: u32 = 1 << 3;
+
+/// One reading of the battery and the AC adapter. Capacities are in mWh
+/// and the rate in mW, positive while charging.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatteryReading {
+    pub battery_present: bool,
+    pub ac_online: bool,
+    pub state: u32,
+    pub percentage: u32,
+    pub remaining_mwh: u32,
+    pub full_charge_mwh: u32,
+    pub design_mwh: u32,
+    pub rate_mw: i32,
+    pub voltage_mv: u32,
+    /// Minutes, 0 when not applicable
+    pub time_to_empty: u32,
+    pub time_to_full: u32,
+}
+
+impl BatteryReading {
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        for value in [
+            self.battery_present as u32,
+            self.ac_online as u32,
+            self.state,
+            self.percentage,
+            self.remaining_mwh,
+            self.full_charge_mwh,
+            self.design_mwh,
+            self.rate_mw as u32,
+            self.voltage_mv,
+            self.time_to_empty,
+            self.time_to_full,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+fn minutes(value: u16) -> u32 {
+    if value == SBS_TIME_UNKNOWN {
+        0
+    } else {
+        value as u32
+    }
+}
+
+/// Read the charger and, when present, the battery
+pub fn read_battery(bus: &mut dyn SmbusWord) -> DriverResult<BatteryReading> {
+    let charger = bus.read_word(SBS_CHARGER_ADDRESS, SBC_CHARGER_STATUS)?;
+    let mut reading = BatteryReading {
+        battery_present: charger & CHARGER_STATUS_BATTERY_PRESENT != 0,
+        ac_online: charger & CHARGER_STATUS_AC_PRESENT != 0,
+        ..BatteryReading::default()
+    };
+    if !reading.battery_present {
+        return Ok(reading);
+    }
+
+    let voltage_mv = bus.read_word(SBS_BATTERY_ADDRESS, SBS_VOLTAGE)? as u32;
+    let current_ma = bus.read_word(SBS_BATTERY_ADDRESS, SBS_CURRENT)? as i16 as i32;
+    let status = bus.read_word(SBS_BATTERY_ADDRESS, SBS_BATTERY_STATUS)?;
+    // Capacities come in mAh unless the battery was switched to 10 mWh units
+    let in_power_units = bus.read_word(SBS_BATTERY_ADDRESS, SBS_BATTERY_MODE)? & BATTERY_MODE_CAPACITY_MODE != 0;
+    let mut capacity = |command| -> DriverResult<u32> {
+        let raw = bus.read_word(SBS_BATTERY_ADDRESS, command)? as u32;
+        Ok(if in_power_units { raw * 10 } else { raw * voltage_mv / 1000 })
+    };
+    reading.remaining_mwh = capacity(SBS_REMAINING_CAPACITY)?;
+    reading.full_charge_mwh = capacity(SBS_FULL_CHARGE_CAPACITY)?;
+    reading.design_mwh = capacity(SBS_DESIGN_CAPACITY)?;
+    reading.percentage = (bus.read_word(SBS_BATTERY_ADDRESS, SBS_RELATIVE_STATE_OF_CHARGE)? as u32).min(100);
+    reading.rate_mw = current_ma * voltage_mv as i32 / 1000;
+    reading.voltage_mv = voltage_mv;
+
+    if status & BATTERY_STATUS_DISCHARGING != 0 {
+        reading.state |= STATE_DISCHARGING;
+        reading.time_to_empty = minutes(bus.read_word(SBS_BATTERY_ADDRESS, SBS_RUN_TIME_TO_EMPTY)?);
+    } else if current_ma > 0 {
+        reading.state |= STATE_CHARGING;
+        reading.time_to_full = minutes(bus.read_word(SBS_BATTERY_ADDRESS, SBS_AVERAGE_TIME_TO_FULL)?);
+    }
+    if status & BATTERY_STATUS_FULLY_CHARGED != 0 {
+        reading.state |= STATE_FULL;
+    }
+    let alarms = BATTERY_STATUS_REMAINING_CAPACITY_ALARM
+        | BATTERY_STATUS_TERMINATE_DISCHARGE_ALARM
+        | BATTERY_STATUS_FULLY_DISCHARGED;
+    if status & alarms != 0 && !reading.ac_online {
+        reading.state |= STATE_CRITICAL;
+    }
+    Ok(reading)
+}
+
+/// Temperature of the battery in millidegrees Celsius
+pub fn read_temperature(bus: &mut dyn SmbusWord) -> DriverResult<i32> {
+    Ok(decikelvin_to_mc(bus.read_word(SBS_BATTERY_ADDRESS, SBS_TEMPERATURE)?))
+}
+
+// ========================================
+// BATTERY SERVICE
+// ========================================
+
+// Opcodes of the "acpi-battery" endpoint
+const OP_READ: u32 = 1;
+
+// Reply status codes
+const STATUS_OK: i32 = 0;
+const STATUS_EIO: i32 = -5;
+const STATUS_EINVAL: i32 = -22;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Thermal server channel, where the battery reports its temperature
+struct ThermalIpc(IpcChannel);
+
+impl orion_thermal::Transport for ThermalIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+struct BatteryServer {
+    ec: EmbeddedController,
+    /// Last reading and when it was taken
+    reading: Option<(BatteryReading, u64)>,
+    thermal_sensor: ThermalSensor,
+    thermal: ThermalIpc,
+    ipc_channel: IpcChannel,
+}
+
+impl BatteryServer {
+    fn new() -> Self {
+        Self {
+            ec: EmbeddedController,
+            reading: None,
+            thermal_sensor: ThermalSensor::new("battery", BATTERY_PASSIVE_MC, BATTERY_CRITICAL_MC),
+            thermal: ThermalIpc(IpcChannel::connect("thermal")),
+            ipc_channel: IpcChannel::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    fn current(&mut self) -> DriverResult<BatteryReading> {
+        let now = monotonic_ns();
+        if let Some((reading, taken)) = self.reading {
+            if now.saturating_sub(taken) < READING_MAX_AGE_NS {
+                return Ok(reading);
+            }
+        }
+        let reading = read_battery(&mut self.ec)?;
+        self.reading = Some((reading, now));
+        if reading.battery_present {
+            if let Ok(temp_mc) = read_temperature(&mut self.ec) {
+                self.thermal_sensor.report(&mut self.thermal, temp_mc, now);
+            }
+        }
+        Ok(reading)
+    }
+
+    /// READ -> battery_present, ac_online, state, percentage, remaining,
+    /// full_charge and design capacity (mWh), rate (mW, i32), voltage (mV),
+    /// time_to_empty and time_to_full (minutes), all u32
+    fn handle_message(&mut self, message: IpcMessage) {
+        let mut payload = Vec::new();
+        let status = match read_u32(&message.data, 0) {
+            Some(OP_READ) => match self.current() {
+                Ok(reading) => {
+                    reading.encode(&mut payload);
+                    STATUS_OK
+                }
+                Err(_) => STATUS_EIO,
+            },
+            _ => STATUS_EINVAL,
+        };
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    let mut server = BatteryServer::new();
+    // No charger answering means no battery subsystem on this machine
+    if read_battery(&mut server.ec).is_err() {
+        return;
+    }
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeBus(Vec<(u8, u8, u16)>);
+
+    impl SmbusWord for FakeBus {
+        fn read_word(&mut self, address: u8, command: u8) -> DriverResult<u16> {
+            self.0
+                .iter()
+                .find(|(a, c, _)| *a == address && *c == command)
+                .map(|(_, _, value)| *value)
+                .ok_or(DriverError::IoError)
+        }
+    }
+
+    fn battery(mode: u16, current: i16, status: u16, charger: u16) -> FakeBus {
+        FakeBus(alloc::vec![
+            (SBS_CHARGER_ADDRESS, SBC_CHARGER_STATUS, charger),
+            (SBS_BATTERY_ADDRESS, SBS_BATTERY_MODE, mode),
+            (SBS_BATTERY_ADDRESS, SBS_VOLTAGE, 12_000),
+            (SBS_BATTERY_ADDRESS, SBS_CURRENT, current as u16),
+            (SBS_BATTERY_ADDRESS, SBS_BATTERY_STATUS, status),
+            (SBS_BATTERY_ADDRESS, SBS_REMAINING_CAPACITY, 1_000),
+            (SBS_BATTERY_ADDRESS, SBS_FULL_CHARGE_CAPACITY, 4_000),
+            (SBS_BATTERY_ADDRESS, SBS_DESIGN_CAPACITY, 5_000),
+            (SBS_BATTERY_ADDRESS, SBS_RELATIVE_STATE_OF_CHARGE, 25),
+            (SBS_BATTERY_ADDRESS, SBS_RUN_TIME_TO_EMPTY, 90),
+            (SBS_BATTERY_ADDRESS, SBS_AVERAGE_TIME_TO_FULL, SBS_TIME_UNKNOWN),
+            (SBS_BATTERY_ADDRESS, SBS_TEMPERATURE, 3_082),
+        ])
+    }
+
+    #[test]
+    fn test_discharging_battery_in_milliamp_hours() {
+        let mut bus = battery(0, -1_500, BATTERY_STATUS_DISCHARGING, CHARGER_STATUS_BATTERY_PRESENT);
+        let reading = read_battery(&mut bus).unwrap();
+        assert!(reading.battery_present && !reading.ac_online);
+        assert_eq!(reading.state, STATE_DISCHARGING);
+        // 1000 mAh at 12 V
+        assert_eq!(reading.remaining_mwh, 12_000);
+        assert_eq!(reading.full_charge_mwh, 48_000);
+        assert_eq!(reading.rate_mw, -18_000);
+        assert_eq!(reading.percentage, 25);
+        assert_eq!(reading.time_to_empty, 90);
+        assert_eq!(read_temperature(&mut bus).unwrap(), 35_050);
+    }
+
+    #[test]
+    fn test_charging_battery_in_power_units() {
+        let charger = CHARGER_STATUS_BATTERY_PRESENT | CHARGER_STATUS_AC_PRESENT;
+        let mut bus = battery(BATTERY_MODE_CAPACITY_MODE, 2_000, BATTERY_STATUS_REMAINING_CAPACITY_ALARM, charger);
+        let reading = read_battery(&mut bus).unwrap();
+        assert!(reading.ac_online);
+        // Alarms do not count as critical on AC power
+        assert_eq!(reading.state, STATE_CHARGING);
+        assert_eq!(reading.remaining_mwh, 10_000);
+        assert_eq!(reading.time_to_full, 0);
+
+        let mut buffer = Vec::new();
+        reading.encode(&mut buffer);
+        assert_eq!(buffer.len(), 44);
+    }
+
+    #[test]
+    fn test_missing_battery() {
+        let mut bus = FakeBus(alloc::vec![(SBS_CHARGER_ADDRESS, SBC_CHARGER_STATUS, CHARGER_STATUS_AC_PRESENT)]);
+        let reading = read_battery(&mut bus).unwrap();
+        assert!(!reading.battery_present && reading.ac_online);
+        assert_eq!(reading.percentage, 0);
+    }
+}
@@ -0,0 +1,452 @@
+/*
+ * Orion Operating System - 16550 Serial Port Driver
+ *
+ * Driver for the 16550A UART of COM1, the serial port of PCs and of every
+ * virtual machine. It is the reference character device driver (see
+ * lib/orion_chardev): the UART implements CharDriver, a CharDevice
+ * serves it on the driver's own "serial" endpoint, and the port is
+ * registered with devfs as /dev/ttyS0. Opening it takes read and write
+ * on the device ttyS0 under the MAC policy (see capabilities/mac.h).
+ *
+ * The port runs at 8 data bits, no parity and one stop bit, with both
+ * FIFOs on. Interrupts are not delivered to character drivers, so while
+ * a handle is open the driver polls the line status every SERIAL_POLL_NS
+ * and moves what arrived into a receive buffer; at 115200 baud the 16
+ * bytes of the receive FIFO last about 1.4 ms. Bytes that do not fit
+ * the buffer, and those the UART itself lost, are counted.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_chardev::{CharDevice, CharDriver, CharError, CharRequest, DevfsClient, DeviceClass, Readiness, Replies};
+use orion_driver::{DriverError, DriverResult};
+use orion_ipc::IpcChannel;
+use orion_mac::{CLASS_DEVICE, PERM_READ, PERM_WRITE};
+use orion_sys::{mac_check, nanosleep};
+
+// Global allocator for the driver
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the handles and the receive buffer
+const HEAP_SIZE: usize = 256 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+/// I/O ports of COM1
+const COM1_BASE: u16 = 0x3F8;
+
+// UART registers (relative to the base port); with LCR_DLAB set the
+// first two are the divisor latch
+const UART_RBR: u16 = 0; // Receive buffer (read)
+const UART_THR: u16 = 0; // Transmit holding (write)
+const UART_DLL: u16 = 0;
+const UART_IER: u16 = 1;
+const UART_DLM: u16 = 1;
+const UART_FCR: u16 = 2;
+const UART_LCR: u16 = 3;
+const UART_MCR: u16 = 4;
+const UART_LSR: u16 = 5;
+const UART_SCR: u16 = 7;
+
+const LCR_8N1: u8 = 0x03;
+const LCR_DLAB: u8 = 0x80;
+
+// FIFOs on and cleared, receive trigger at 14 bytes
+const FCR_ENABLE: u8 = 0x01;
+const FCR_CLEAR_RX: u8 = 0x02;
+const FCR_CLEAR_TX: u8 = 0x04;
+const FCR_TRIGGER_14: u8 = 0xC0;
+
+// DTR, RTS and OUT2
+const MCR_DTR_RTS_OUT2: u8 = 0x0B;
+
+const LSR_DATA_READY: u8 = 0x01;
+const LSR_OVERRUN: u8 = 0x02;
+const LSR_THR_EMPTY: u8 = 0x20;
+
+/// Bytes the transmit FIFO takes once empty
+const TX_FIFO_SIZE: usize = 16;
+
+/// Baud rate of a divisor of 1 (1.8432 MHz clock / 16)
+const UART_BASE_BAUD: u32 = 115_200;
+const DEFAULT_BAUD: u32 = 115_200;
+
+/// Bytes received and not read yet that the driver keeps
+const RX_BUFFER_SIZE: usize = 4096;
+
+/// How often the line is checked while a handle is open
+const SERIAL_POLL_NS: u64 = 500_000;
+
+// Ioctl commands
+//
+//   SERIAL_GET_CONFIG           -> baud:u32 overruns:u32 dropped:u32
+//   SERIAL_SET_BAUD   baud:u32  -> (empty)
+//
+// SET_BAUD takes the rates a divisor of the 115200 base gives exactly.
+pub const SERIAL_GET_CONFIG: u32 = 0x5301;
+pub const SERIAL_SET_BAUD: u32 = 0x5302;
+
+const DEVICE_NAME: &str = "ttyS0";
+const ENDPOINT_NAME: &str = "serial";
+
+// ========================================
+// UART
+// ========================================
+
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    core::arch::asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn outb(port: u16, value: u8) {
+    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+/// Register access, abstracted so the driver runs against a simulated
+/// UART in the tests
+pub trait UartRegisters {
+    fn read(&mut self, register: u16) -> u8;
+    fn write(&mut self, register: u16, value: u8);
+}
+
+pub struct PortIo {
+    base: u16,
+}
+
+impl UartRegisters for PortIo {
+    fn read(&mut self, register: u16) -> u8 {
+        unsafe { inb(self.base + register) }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        unsafe { outb(self.base + register, value) }
+    }
+}
+
+pub struct Uart<R: UartRegisters> {
+    registers: R,
+    baud: u32,
+    rx: VecDeque<u8>,
+    /// Bytes the UART lost, as far as it tells: one per overrun
+    overruns: u32,
+    /// Bytes dropped with the receive buffer full
+    dropped: u32,
+}
+
+impl<R: UartRegisters> Uart<R> {
+    /// Check a UART answers and set it up
+    pub fn probe(mut registers: R) -> DriverResult<Self> {
+        // The scratch register keeps what is written to it; nothing
+        // decoding the ports reads back all ones
+        registers.write(UART_SCR, 0x5A);
+        if registers.read(UART_SCR) != 0x5A {
+            return Err(DriverError::IoError);
+        }
+        let mut uart = Self { registers, baud: 0, rx: VecDeque::new(), overruns: 0, dropped: 0 };
+        uart.registers.write(UART_IER, 0);
+        uart.set_baud(DEFAULT_BAUD).map_err(|_| DriverError::InvalidParameter)?;
+        uart.registers.write(UART_FCR, FCR_ENABLE | FCR_CLEAR_RX | FCR_CLEAR_TX | FCR_TRIGGER_14);
+        uart.registers.write(UART_MCR, MCR_DTR_RTS_OUT2);
+        Ok(uart)
+    }
+
+    pub fn baud(&self) -> u32 {
+        self.baud
+    }
+
+    pub fn set_baud(&mut self, baud: u32) -> Result<(), CharError> {
+        if baud == 0 || baud > UART_BASE_BAUD || !UART_BASE_BAUD.is_multiple_of(baud) {
+            return Err(CharError::InvalidArgument);
+        }
+        let divisor = UART_BASE_BAUD / baud;
+        self.registers.write(UART_LCR, LCR_DLAB);
+        self.registers.write(UART_DLL, divisor as u8);
+        self.registers.write(UART_DLM, (divisor >> 8) as u8);
+        self.registers.write(UART_LCR, LCR_8N1);
+        self.baud = baud;
+        Ok(())
+    }
+
+    /// Reading the line status clears its overrun bit, so every read
+    /// counts it
+    fn line_status(&mut self) -> u8 {
+        let status = self.registers.read(UART_LSR);
+        if status & LSR_OVERRUN != 0 {
+            self.overruns = self.overruns.wrapping_add(1);
+        }
+        status
+    }
+
+    /// Move what the receive FIFO holds into the buffer
+    pub fn service(&mut self) {
+        loop {
+            if self.line_status() & LSR_DATA_READY == 0 {
+                return;
+            }
+            let byte = self.registers.read(UART_RBR);
+            if self.rx.len() < RX_BUFFER_SIZE {
+                self.rx.push_back(byte);
+            } else {
+                self.dropped = self.dropped.wrapping_add(1);
+            }
+        }
+    }
+
+    fn transmitter_empty(&mut self) -> bool {
+        self.line_status() & LSR_THR_EMPTY != 0
+    }
+}
+
+impl<R: UartRegisters> CharDriver for Uart<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, CharError> {
+        self.service();
+        if self.rx.is_empty() {
+            return Err(CharError::WouldBlock);
+        }
+        let count = buffer.len().min(self.rx.len());
+        for (slot, byte) in buffer.iter_mut().zip(self.rx.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize, CharError> {
+        if !self.transmitter_empty() {
+            return Err(CharError::WouldBlock);
+        }
+        let count = data.len().min(TX_FIFO_SIZE);
+        for &byte in &data[..count] {
+            self.registers.write(UART_THR, byte);
+        }
+        Ok(count)
+    }
+
+    fn poll(&mut self) -> Readiness {
+        self.service();
+        let mut ready = Readiness::NONE;
+        if !self.rx.is_empty() {
+            ready = ready | Readiness::READABLE;
+        }
+        if self.transmitter_empty() {
+            ready = ready | Readiness::WRITABLE;
+        }
+        ready
+    }
+
+    fn ioctl(&mut self, command: u32, argument: &[u8]) -> Result<Vec<u8>, CharError> {
+        match command {
+            SERIAL_GET_CONFIG => {
+                let mut payload = Vec::with_capacity(12);
+                for value in [self.baud, self.overruns, self.dropped] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                Ok(payload)
+            }
+            SERIAL_SET_BAUD => {
+                let bytes = argument.get(..4).ok_or(CharError::InvalidArgument)?;
+                self.set_baud(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))?;
+                Ok(Vec::new())
+            }
+            _ => Err(CharError::NotSupported),
+        }
+    }
+
+    /// What arrived for the last user is not handed to the next one
+    fn release(&mut self, open_handles: usize) {
+        if open_handles == 0 {
+            self.rx.clear();
+        }
+    }
+}
+
+// ========================================
+// SERIAL SERVICE
+// ========================================
+
+/// File system server channel, where the port is registered
+struct FsIpc(IpcChannel);
+
+impl orion_chardev::Transport for FsIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Requests on handles were admitted when their handle was opened
+fn admit(sender: u64, request: &[u8]) -> Result<(), i32> {
+    match CharRequest::decode(request) {
+        Some(CharRequest::Open { .. }) => mac_check(sender, CLASS_DEVICE, DEVICE_NAME, PERM_READ | PERM_WRITE),
+        _ => Ok(()),
+    }
+}
+
+struct SerialServer {
+    device: CharDevice<Uart<PortIo>>,
+    ipc_channel: IpcChannel,
+}
+
+impl SerialServer {
+    fn send(&self, replies: Replies) {
+        for (receiver, response) in replies {
+            self.ipc_channel.send(receiver, &response);
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            self.device.driver_mut().service();
+            let replies = self.device.wake();
+            self.send(replies);
+            match self.ipc_channel.receive() {
+                Some(message) => {
+                    let replies = match admit(message.sender, &message.data) {
+                        Ok(()) => self.device.handle(message.sender, &message.data),
+                        Err(status) => vec![(message.sender, status.to_le_bytes().to_vec())],
+                    };
+                    self.send(replies);
+                }
+                // Keep draining the FIFO while someone may read
+                None if self.device.open_handles() > 0 => {
+                    let _ = nanosleep(SERIAL_POLL_NS);
+                }
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let Ok(uart) = Uart::probe(PortIo { base: COM1_BASE }) else {
+        return;
+    };
+    let mut server = SerialServer { device: CharDevice::new(uart), ipc_channel: IpcChannel::new() };
+    let mut devfs = DevfsClient::new(FsIpc(IpcChannel::connect("fs")));
+    if devfs.register(DEVICE_NAME, DeviceClass::Serial, ENDPOINT_NAME).is_err() {
+        return;
+    }
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A UART with what the line will bring in `incoming`, taking a byte
+    /// into its receive FIFO each time the line status is read
+    #[derive(Default)]
+    struct FakeUart {
+        registers: [u8; 8],
+        divisor: [u8; 2],
+        incoming: VecDeque<u8>,
+        fifo: VecDeque<u8>,
+        sent: Vec<u8>,
+        transmitting: bool,
+    }
+
+    impl UartRegisters for FakeUart {
+        fn read(&mut self, register: u16) -> u8 {
+            match register {
+                UART_LSR => {
+                    if let Some(byte) = self.incoming.pop_front() {
+                        self.fifo.push_back(byte);
+                    }
+                    let mut status = if self.transmitting { 0 } else { LSR_THR_EMPTY };
+                    if !self.fifo.is_empty() {
+                        status |= LSR_DATA_READY;
+                    }
+                    status
+                }
+                UART_RBR => self.fifo.pop_front().unwrap_or(0),
+                _ => self.registers[register as usize],
+            }
+        }
+
+        fn write(&mut self, register: u16, value: u8) {
+            let latched = self.registers[UART_LCR as usize] & LCR_DLAB != 0;
+            match register {
+                UART_DLL | UART_DLM if latched => self.divisor[register as usize] = value,
+                UART_THR => self.sent.push(value),
+                _ => self.registers[register as usize] = value,
+            }
+        }
+    }
+
+    #[test]
+    fn test_probe_programs_the_line() {
+        let uart = Uart::probe(FakeUart::default()).unwrap();
+        assert_eq!(uart.baud(), 115_200);
+        assert_eq!(uart.registers.divisor, [1, 0]);
+        assert_eq!(uart.registers.registers[UART_LCR as usize], LCR_8N1);
+
+        // Nothing answers on the ports
+        struct Absent;
+        impl UartRegisters for Absent {
+            fn read(&mut self, _register: u16) -> u8 {
+                0xFF
+            }
+            fn write(&mut self, _register: u16, _value: u8) {}
+        }
+        assert!(Uart::probe(Absent).is_err());
+    }
+
+    #[test]
+    fn test_serves_reads_writes_and_ioctls() {
+        let mut device = CharDevice::new(Uart::probe(FakeUart::default()).unwrap());
+        let replies = device.handle(5, &CharRequest::Open { flags: 0 }.encode());
+        let handle = u32::from_le_bytes(replies[0].1[4..8].try_into().unwrap());
+
+        // A read waits for the line; a write goes out a FIFO at a time
+        assert!(device.handle(5, &CharRequest::Read { handle, length: 64 }.encode()).is_empty());
+        device.driver_mut().registers.incoming.extend(b"login\n");
+        device.driver_mut().service();
+        let replies = device.wake();
+        assert_eq!(&replies[0].1[4..], b"login\n");
+        let banner = [b'#'; 40];
+        device.driver_mut().registers.transmitting = true;
+        assert!(device.handle(5, &CharRequest::Write { handle, data: banner.to_vec() }.encode()).is_empty());
+        device.driver_mut().registers.transmitting = false;
+        let replies = device.wake();
+        assert_eq!(u32::from_le_bytes(replies[0].1[4..8].try_into().unwrap()), 40);
+        assert_eq!(device.driver().registers.sent, banner);
+
+        let set = CharRequest::Ioctl { handle, command: SERIAL_SET_BAUD, argument: 9600u32.to_le_bytes().to_vec() };
+        assert_eq!(&device.handle(5, &set.encode())[0].1[..4], &0i32.to_le_bytes());
+        assert_eq!(device.driver().registers.divisor, [12, 0]);
+        let odd = CharRequest::Ioctl { handle, command: SERIAL_SET_BAUD, argument: 7000u32.to_le_bytes().to_vec() };
+        assert_eq!(&device.handle(5, &odd.encode())[0].1[..4], &CharError::InvalidArgument.status().to_le_bytes());
+        let get = CharRequest::Ioctl { handle, command: SERIAL_GET_CONFIG, argument: Vec::new() };
+        assert_eq!(&device.handle(5, &get.encode())[0].1[4..8], &9600u32.to_le_bytes());
+    }
+}
@@ -0,0 +1,1170 @@
+/*
+ * Orion Operating System - TPM 2.0 Driver
+ *
+ * Driver for TPM 2.0 devices behind the TIS (FIFO) or CRB interface, with
+ * the small part of the TPM Software Stack Orion needs: startup, PCR
+ * extend/read, random numbers, quotes and sealing under the storage root
+ * key. The driver replays the kernel measured boot event log into the PCRs
+ * and serves the attestation API used by remote verifiers.
+ *
+ * The TPM is a platform device described by the ACPI TPM2 table rather than
+ * a PCI function, so the driver serves its own IPC endpoint instead of going
+ * through bus probing.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_driver::{DriverError, DriverResult, MmioAccessor, MmioPermissions};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_cap::Capability;
+use orion_crypto::wipe;
+use orion_proto::reply;
+use orion_proto::rights::CAP_ADMIN;
+use orion_sys::{measure_log, MeasureEvent};
+
+// Global allocator for the driver
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+// ========================================
+// HARDWARE CONSTANTS
+// ========================================
+
+/// Fixed locality 0 register window on PC platforms (TIS and CRB alike)
+const TPM_BASE_ADDRESS: u64 = 0xFED4_0000;
+const TPM_WINDOW_SIZE: usize = 0x1000;
+
+// Registers shared by both interfaces
+const TPM_INTERFACE_ID: u64 = 0x030;
+const TPM_DID_VID: u64 = 0xF00;
+
+// INTERFACE_ID.InterfaceType
+const TPM_INTERFACE_TYPE_FIFO: u32 = 0x0;
+const TPM_INTERFACE_TYPE_CRB: u32 = 0x1;
+const TPM_INTERFACE_TYPE_TIS: u32 = 0xF;
+
+// TIS registers (locality 0)
+const TIS_ACCESS: u64 = 0x000;
+const TIS_STS: u64 = 0x018;
+const TIS_DATA_FIFO: u64 = 0x024;
+
+const TIS_ACCESS_VALID: u8 = 0x80;
+const TIS_ACCESS_ACTIVE_LOCALITY: u8 = 0x20;
+const TIS_ACCESS_REQUEST_USE: u8 = 0x02;
+
+const TIS_STS_VALID: u32 = 0x80;
+const TIS_STS_COMMAND_READY: u32 = 0x40;
+const TIS_STS_GO: u32 = 0x20;
+const TIS_STS_DATA_AVAIL: u32 = 0x10;
+const TIS_STS_EXPECT: u32 = 0x08;
+
+// CRB registers (locality 0)
+const CRB_LOC_STATE: u64 = 0x000;
+const CRB_LOC_CTRL: u64 = 0x008;
+const CRB_CTRL_REQ: u64 = 0x040;
+const CRB_CTRL_STS: u64 = 0x044;
+const CRB_CTRL_START: u64 = 0x04C;
+const CRB_CTRL_CMD_SIZE: u64 = 0x058;
+const CRB_CTRL_CMD_LADDR: u64 = 0x05C;
+const CRB_CTRL_CMD_HADDR: u64 = 0x060;
+const CRB_CTRL_RSP_SIZE: u64 = 0x064;
+const CRB_CTRL_RSP_ADDR: u64 = 0x068;
+
+const CRB_LOC_STATE_ASSIGNED: u32 = 0x02;
+const CRB_LOC_CTRL_REQUEST: u32 = 0x01;
+const CRB_CTRL_REQ_CMD_READY: u32 = 0x01;
+const CRB_CTRL_REQ_GO_IDLE: u32 = 0x02;
+const CRB_CTRL_STS_ERROR: u32 = 0x01;
+
+/// Polling budget for every wait on the device
+const TPM_POLL_ITERATIONS: u32 = 1_000_000;
+
+// ========================================
+// TPM 2.0 CONSTANTS
+// ========================================
+
+const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+const TPM_ST_SESSIONS: u16 = 0x8002;
+
+const TPM_CC_CREATE_PRIMARY: u32 = 0x0000_0131;
+const TPM_CC_SELF_TEST: u32 = 0x0000_0143;
+const TPM_CC_STARTUP: u32 = 0x0000_0144;
+const TPM_CC_CREATE: u32 = 0x0000_0153;
+const TPM_CC_LOAD: u32 = 0x0000_0157;
+const TPM_CC_QUOTE: u32 = 0x0000_0158;
+const TPM_CC_UNSEAL: u32 = 0x0000_015E;
+const TPM_CC_FLUSH_CONTEXT: u32 = 0x0000_0165;
+const TPM_CC_GET_RANDOM: u32 = 0x0000_017B;
+const TPM_CC_PCR_READ: u32 = 0x0000_017E;
+const TPM_CC_PCR_EXTEND: u32 = 0x0000_0182;
+
+const TPM_RC_SUCCESS: u32 = 0x000;
+const TPM_RC_INITIALIZE: u32 = 0x100;
+
+const TPM_SU_CLEAR: u16 = 0x0000;
+const TPM_RS_PW: u32 = 0x4000_0009;
+const TPM_RH_OWNER: u32 = 0x4000_0001;
+const TPM_RH_ENDORSEMENT: u32 = 0x4000_000B;
+
+const TPM_ALG_AES: u16 = 0x0006;
+const TPM_ALG_KEYEDHASH: u16 = 0x0008;
+const TPM_ALG_SHA256: u16 = 0x000B;
+const TPM_ALG_NULL: u16 = 0x0010;
+const TPM_ALG_ECDSA: u16 = 0x0018;
+const TPM_ALG_ECC: u16 = 0x0023;
+const TPM_ALG_CFB: u16 = 0x0043;
+const TPM_ECC_NIST_P256: u16 = 0x0003;
+
+// TPMA_OBJECT bits
+const TPMA_FIXED_TPM: u32 = 1 << 1;
+const TPMA_FIXED_PARENT: u32 = 1 << 4;
+const TPMA_SENSITIVE_DATA_ORIGIN: u32 = 1 << 5;
+const TPMA_USER_WITH_AUTH: u32 = 1 << 6;
+const TPMA_NO_DA: u32 = 1 << 10;
+const TPMA_RESTRICTED: u32 = 1 << 16;
+const TPMA_DECRYPT: u32 = 1 << 17;
+const TPMA_SIGN: u32 = 1 << 18;
+
+const SHA256_DIGEST_SIZE: usize = 32;
+const TPM_PCR_COUNT: u32 = 24;
+const TPM_MAX_RESPONSE: usize = 4096;
+
+/// Largest secret a sealed data object may hold (MAX_SYM_DATA)
+pub const TPM_MAX_SEALED_DATA: usize = 128;
+
+/// Largest nonce accepted in a quote request
+const TPM_MAX_NONCE: usize = 64;
+
+// ========================================
+// TSS: COMMAND MARSHALLING
+// ========================================
+
+/// Big-endian command builder; the size field is patched by `finish`
+pub struct CommandBuffer {
+    bytes: Vec<u8>,
+}
+
+impl CommandBuffer {
+    pub fn new(tag: u16, command_code: u32) -> Self {
+        let mut buffer = Self { bytes: Vec::with_capacity(64) };
+        buffer.put_u16(tag);
+        buffer.put_u32(0);
+        buffer.put_u32(command_code);
+        buffer
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn put_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_bytes(&mut self, value: &[u8]) {
+        self.bytes.extend_from_slice(value);
+    }
+
+    /// TPM2B: 16-bit length followed by the bytes
+    pub fn put_tpm2b(&mut self, value: &[u8]) {
+        self.put_u16(value.len() as u16);
+        self.put_bytes(value);
+    }
+
+    /// Authorization area holding a single empty password session
+    pub fn put_password_session(&mut self) {
+        self.put_u32(9);
+        self.put_u32(TPM_RS_PW);
+        self.put_u16(0); // nonce
+        self.put_u8(0); // session attributes
+        self.put_u16(0); // hmac
+    }
+
+    /// TPML_PCR_SELECTION for the SHA-256 bank
+    pub fn put_pcr_selection(&mut self, mask: u32) {
+        self.put_u32(1);
+        self.put_u16(TPM_ALG_SHA256);
+        self.put_u8(3);
+        self.put_bytes(&[mask as u8, (mask >> 8) as u8, (mask >> 16) as u8]);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let size = self.bytes.len() as u32;
+        self.bytes[2..6].copy_from_slice(&size.to_be_bytes());
+        self.bytes
+    }
+}
+
+/// Big-endian response reader positioned after the header
+pub struct ResponseReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ResponseReader<'a> {
+    /// Validate the header and return a reader over the response body
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, u32> {
+        if bytes.len() < 10 {
+            return Err(u32::MAX);
+        }
+        let size = u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]) as usize;
+        let code = u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+        if size > bytes.len() || size < 10 {
+            return Err(u32::MAX);
+        }
+        if code != TPM_RC_SUCCESS {
+            return Err(code);
+        }
+        Ok(Self { bytes: &bytes[..size], offset: 10 })
+    }
+
+    pub fn get_u8(&mut self) -> Option<u8> {
+        let value = *self.bytes.get(self.offset)?;
+        self.offset += 1;
+        Some(value)
+    }
+
+    pub fn get_u16(&mut self) -> Option<u16> {
+        let bytes = self.get_bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn get_u32(&mut self) -> Option<u32> {
+        let bytes = self.get_bytes(4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn get_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.offset..self.offset + len)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    pub fn get_tpm2b(&mut self) -> Option<&'a [u8]> {
+        let len = self.get_u16()? as usize;
+        self.get_bytes(len)
+    }
+
+    /// Skip a TPM2B, returning it together with its length prefix
+    pub fn get_tpm2b_raw(&mut self) -> Option<&'a [u8]> {
+        let start = self.offset;
+        self.get_tpm2b()?;
+        Some(&self.bytes[start..self.offset])
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.offset..]
+    }
+}
+
+pub fn build_startup() -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_CC_STARTUP);
+    command.put_u16(TPM_SU_CLEAR);
+    command.finish()
+}
+
+pub fn build_self_test() -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_CC_SELF_TEST);
+    command.put_u8(1); // fullTest = YES
+    command.finish()
+}
+
+pub fn build_pcr_extend(pcr: u32, digest: &[u8; SHA256_DIGEST_SIZE]) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_SESSIONS, TPM_CC_PCR_EXTEND);
+    command.put_u32(pcr);
+    command.put_password_session();
+    command.put_u32(1);
+    command.put_u16(TPM_ALG_SHA256);
+    command.put_bytes(digest);
+    command.finish()
+}
+
+pub fn build_pcr_read(mask: u32) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_CC_PCR_READ);
+    command.put_pcr_selection(mask);
+    command.finish()
+}
+
+pub fn build_get_random(bytes: u16) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_CC_GET_RANDOM);
+    command.put_u16(bytes);
+    command.finish()
+}
+
+pub fn build_flush_context(handle: u32) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_CC_FLUSH_CONTEXT);
+    command.put_u32(handle);
+    command.finish()
+}
+
+/// Append an ECC P-256 TPMT_PUBLIC for either the storage root key or the
+/// attestation key
+fn put_ecc_template(command: &mut CommandBuffer, signing: bool) {
+    let mut template = CommandBuffer { bytes: Vec::with_capacity(32) };
+    let common = TPMA_FIXED_TPM | TPMA_FIXED_PARENT | TPMA_SENSITIVE_DATA_ORIGIN
+        | TPMA_USER_WITH_AUTH | TPMA_RESTRICTED;
+
+    template.put_u16(TPM_ALG_ECC);
+    template.put_u16(TPM_ALG_SHA256);
+    if signing {
+        template.put_u32(common | TPMA_SIGN);
+        template.put_tpm2b(&[]); // authPolicy
+        template.put_u16(TPM_ALG_NULL); // symmetric
+        template.put_u16(TPM_ALG_ECDSA);
+        template.put_u16(TPM_ALG_SHA256);
+    } else {
+        template.put_u32(common | TPMA_DECRYPT | TPMA_NO_DA);
+        template.put_tpm2b(&[]);
+        template.put_u16(TPM_ALG_AES);
+        template.put_u16(128);
+        template.put_u16(TPM_ALG_CFB);
+        template.put_u16(TPM_ALG_NULL); // scheme
+    }
+    template.put_u16(TPM_ECC_NIST_P256);
+    template.put_u16(TPM_ALG_NULL); // kdf
+    template.put_tpm2b(&[]); // unique.x
+    template.put_tpm2b(&[]); // unique.y
+
+    command.put_tpm2b(&template.bytes);
+}
+
+pub fn build_create_primary(hierarchy: u32, signing: bool) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_SESSIONS, TPM_CC_CREATE_PRIMARY);
+    command.put_u32(hierarchy);
+    command.put_password_session();
+    command.put_u16(4); // inSensitive: empty userAuth and data
+    command.put_u16(0);
+    command.put_u16(0);
+    put_ecc_template(&mut command, signing);
+    command.put_tpm2b(&[]); // outsideInfo
+    command.put_u32(0); // creationPCR
+    command.finish()
+}
+
+pub fn build_quote(ak_handle: u32, nonce: &[u8], mask: u32) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_SESSIONS, TPM_CC_QUOTE);
+    command.put_u32(ak_handle);
+    command.put_password_session();
+    command.put_tpm2b(nonce);
+    command.put_u16(TPM_ALG_NULL); // use the key's scheme
+    command.put_pcr_selection(mask);
+    command.finish()
+}
+
+pub fn build_create_sealed(parent: u32, data: &[u8]) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_SESSIONS, TPM_CC_CREATE);
+    command.put_u32(parent);
+    command.put_password_session();
+    command.put_u16((4 + data.len()) as u16);
+    command.put_tpm2b(&[]); // userAuth
+    command.put_tpm2b(data);
+
+    let mut template = CommandBuffer { bytes: Vec::with_capacity(16) };
+    template.put_u16(TPM_ALG_KEYEDHASH);
+    template.put_u16(TPM_ALG_SHA256);
+    template.put_u32(TPMA_FIXED_TPM | TPMA_FIXED_PARENT | TPMA_USER_WITH_AUTH | TPMA_NO_DA);
+    template.put_tpm2b(&[]);
+    template.put_u16(TPM_ALG_NULL);
+    template.put_tpm2b(&[]);
+    command.put_tpm2b(&template.bytes);
+
+    command.put_tpm2b(&[]);
+    command.put_u32(0);
+    command.finish()
+}
+
+/// `blob` is the TPM2B_PRIVATE followed by the TPM2B_PUBLIC returned by Create
+pub fn build_load(parent: u32, blob: &[u8]) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_SESSIONS, TPM_CC_LOAD);
+    command.put_u32(parent);
+    command.put_password_session();
+    command.put_bytes(blob);
+    command.finish()
+}
+
+pub fn build_unseal(handle: u32) -> Vec<u8> {
+    let mut command = CommandBuffer::new(TPM_ST_SESSIONS, TPM_CC_UNSEAL);
+    command.put_u32(handle);
+    command.put_password_session();
+    command.finish()
+}
+
+// ========================================
+// TRANSPORTS
+// ========================================
+
+/// Moves one command to the TPM and returns the full response
+pub trait TpmTransport {
+    fn transmit(&mut self, command: &[u8]) -> DriverResult<Vec<u8>>;
+}
+
+/// TIS / PTP FIFO interface
+pub struct TisTransport {
+    mmio: MmioAccessor,
+}
+
+impl TisTransport {
+    fn new(mmio: MmioAccessor) -> DriverResult<Self> {
+        let transport = Self { mmio };
+        transport.request_locality()?;
+        Ok(transport)
+    }
+
+    fn request_locality(&self) -> DriverResult<()> {
+        self.mmio.write_u8(TIS_ACCESS, TIS_ACCESS_REQUEST_USE)?;
+        for _ in 0..TPM_POLL_ITERATIONS {
+            let access = self.mmio.read_u8(TIS_ACCESS)?;
+            if access & (TIS_ACCESS_VALID | TIS_ACCESS_ACTIVE_LOCALITY)
+                == (TIS_ACCESS_VALID | TIS_ACCESS_ACTIVE_LOCALITY)
+            {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+
+    fn wait_status(&self, mask: u32) -> DriverResult<u32> {
+        for _ in 0..TPM_POLL_ITERATIONS {
+            let status = self.mmio.read_u32(TIS_STS)?;
+            if status & mask == mask {
+                return Ok(status);
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+
+    fn burst_count(&self) -> DriverResult<usize> {
+        for _ in 0..TPM_POLL_ITERATIONS {
+            let burst = ((self.mmio.read_u32(TIS_STS)? >> 8) & 0xFFFF) as usize;
+            if burst > 0 {
+                return Ok(burst);
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+
+    fn read_fifo(&self, out: &mut Vec<u8>, len: usize) -> DriverResult<()> {
+        while out.len() < len {
+            let burst = self.burst_count()?;
+            for _ in 0..core::cmp::min(burst, len - out.len()) {
+                out.push(self.mmio.read_u8(TIS_DATA_FIFO)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TpmTransport for TisTransport {
+    fn transmit(&mut self, command: &[u8]) -> DriverResult<Vec<u8>> {
+        self.mmio.write_u32(TIS_STS, TIS_STS_COMMAND_READY)?;
+        self.wait_status(TIS_STS_COMMAND_READY)?;
+
+        let mut sent = 0;
+        while sent < command.len() {
+            let burst = self.burst_count()?;
+            let chunk = core::cmp::min(burst, command.len() - sent);
+            for byte in &command[sent..sent + chunk] {
+                self.mmio.write_u8(TIS_DATA_FIFO, *byte)?;
+            }
+            sent += chunk;
+        }
+
+        // The TPM must not expect more data once the whole command is in
+        let status = self.wait_status(TIS_STS_VALID)?;
+        if status & TIS_STS_EXPECT != 0 {
+            return Err(DriverError::IoError);
+        }
+
+        self.mmio.write_u32(TIS_STS, TIS_STS_GO)?;
+        self.wait_status(TIS_STS_VALID | TIS_STS_DATA_AVAIL)?;
+
+        let mut response = Vec::with_capacity(64);
+        self.read_fifo(&mut response, 10)?;
+        let size = u32::from_be_bytes([response[2], response[3], response[4], response[5]]) as usize;
+        if !(10..=TPM_MAX_RESPONSE).contains(&size) {
+            return Err(DriverError::InvalidData);
+        }
+        self.read_fifo(&mut response, size)?;
+
+        self.mmio.write_u32(TIS_STS, TIS_STS_COMMAND_READY)?;
+        Ok(response)
+    }
+}
+
+/// Command Response Buffer interface
+pub struct CrbTransport {
+    mmio: MmioAccessor,
+    command_offset: u64,
+    command_size: usize,
+    response_offset: u64,
+    response_size: usize,
+}
+
+impl CrbTransport {
+    fn new(mmio: MmioAccessor) -> DriverResult<Self> {
+        mmio.write_u32(CRB_LOC_CTRL, CRB_LOC_CTRL_REQUEST)?;
+        let mut assigned = false;
+        for _ in 0..TPM_POLL_ITERATIONS {
+            if mmio.read_u32(CRB_LOC_STATE)? & CRB_LOC_STATE_ASSIGNED != 0 {
+                assigned = true;
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        if !assigned {
+            return Err(DriverError::Timeout);
+        }
+
+        // Buffers must live inside the mapped locality window
+        let command_address = (mmio.read_u32(CRB_CTRL_CMD_HADDR)? as u64) << 32
+            | mmio.read_u32(CRB_CTRL_CMD_LADDR)? as u64;
+        let response_address = mmio.read_u64(CRB_CTRL_RSP_ADDR)?;
+        let command_size = mmio.read_u32(CRB_CTRL_CMD_SIZE)? as usize;
+        let response_size = mmio.read_u32(CRB_CTRL_RSP_SIZE)? as usize;
+
+        let window = TPM_BASE_ADDRESS..TPM_BASE_ADDRESS + TPM_WINDOW_SIZE as u64;
+        if !window.contains(&command_address) || !window.contains(&response_address) {
+            return Err(DriverError::Unsupported);
+        }
+
+        Ok(Self {
+            mmio,
+            command_offset: command_address - TPM_BASE_ADDRESS,
+            command_size,
+            response_offset: response_address - TPM_BASE_ADDRESS,
+            response_size,
+        })
+    }
+
+    fn wait_clear(&self, register: u64, mask: u32) -> DriverResult<()> {
+        for _ in 0..TPM_POLL_ITERATIONS {
+            if self.mmio.read_u32(register)? & mask == 0 {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+}
+
+impl TpmTransport for CrbTransport {
+    fn transmit(&mut self, command: &[u8]) -> DriverResult<Vec<u8>> {
+        if command.len() > self.command_size {
+            return Err(DriverError::BufferTooSmall);
+        }
+
+        self.mmio.write_u32(CRB_CTRL_REQ, CRB_CTRL_REQ_CMD_READY)?;
+        self.wait_clear(CRB_CTRL_REQ, CRB_CTRL_REQ_CMD_READY)?;
+        if self.mmio.read_u32(CRB_CTRL_STS)? & CRB_CTRL_STS_ERROR != 0 {
+            return Err(DriverError::DeviceError);
+        }
+
+        for (i, byte) in command.iter().enumerate() {
+            self.mmio.write_u8(self.command_offset + i as u64, *byte)?;
+        }
+        self.mmio.write_u32(CRB_CTRL_START, 1)?;
+        self.wait_clear(CRB_CTRL_START, 1)?;
+
+        let mut header = [0u8; 10];
+        for (i, byte) in header.iter_mut().enumerate() {
+            *byte = self.mmio.read_u8(self.response_offset + i as u64)?;
+        }
+        let size = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+        if size < 10 || size > self.response_size {
+            return Err(DriverError::InvalidData);
+        }
+
+        let mut response = Vec::with_capacity(size);
+        response.extend_from_slice(&header);
+        for i in 10..size {
+            response.push(self.mmio.read_u8(self.response_offset + i as u64)?);
+        }
+
+        self.mmio.write_u32(CRB_CTRL_REQ, CRB_CTRL_REQ_GO_IDLE)?;
+        Ok(response)
+    }
+}
+
+// ========================================
+// TPM DEVICE
+// ========================================
+
+/// One measurement as recorded in the event log
+pub struct LogEntry {
+    pub pcr: u32,
+    pub event_type: u32,
+    pub digest: [u8; SHA256_DIGEST_SIZE],
+    pub description: String,
+}
+
+/// Result of a quote, returned verbatim to the verifier
+pub struct Quote {
+    pub attest: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+pub struct Tpm {
+    transport: Box<dyn TpmTransport>,
+    srk_handle: Option<u32>,
+    ak_handle: Option<u32>,
+    ak_public: Vec<u8>,
+    last_error: u32,
+}
+
+impl Tpm {
+    /// Identify the interface at the fixed address and start the TPM
+    pub fn probe() -> DriverResult<Self> {
+        let mmio = unsafe {
+            MmioAccessor::new(
+                TPM_BASE_ADDRESS,
+                TPM_WINDOW_SIZE,
+                MmioPermissions::READ | MmioPermissions::WRITE | MmioPermissions::UNCACHED
+            )
+        };
+
+        let did_vid = mmio.read_u32(TPM_DID_VID)?;
+        if did_vid == 0 || did_vid == 0xFFFF_FFFF {
+            return Err(DriverError::DeviceNotFound);
+        }
+
+        let interface = mmio.read_u32(TPM_INTERFACE_ID)? & 0xF;
+        let transport: Box<dyn TpmTransport> = match interface {
+            TPM_INTERFACE_TYPE_CRB => Box::new(CrbTransport::new(mmio)?),
+            TPM_INTERFACE_TYPE_FIFO | TPM_INTERFACE_TYPE_TIS => Box::new(TisTransport::new(mmio)?),
+            _ => return Err(DriverError::UnsupportedDevice),
+        };
+
+        let mut tpm = Self {
+            transport,
+            srk_handle: None,
+            ak_handle: None,
+            ak_public: Vec::new(),
+            last_error: 0,
+        };
+        tpm.startup()?;
+        Ok(tpm)
+    }
+
+    fn execute(&mut self, command: &[u8]) -> DriverResult<Vec<u8>> {
+        let response = self.transport.transmit(command)?;
+        match ResponseReader::parse(&response) {
+            Ok(_) => Ok(response),
+            Err(code) => {
+                self.last_error = code;
+                Err(DriverError::DeviceError)
+            }
+        }
+    }
+
+    fn startup(&mut self) -> DriverResult<()> {
+        // Firmware normally issued Startup already; TPM_RC_INITIALIZE says so
+        let response = self.transport.transmit(&build_startup())?;
+        match ResponseReader::parse(&response) {
+            Ok(_) | Err(TPM_RC_INITIALIZE) => {}
+            Err(code) => {
+                self.last_error = code;
+                return Err(DriverError::InitializationFailed);
+            }
+        }
+        self.execute(&build_self_test())?;
+        Ok(())
+    }
+
+    pub fn pcr_extend(&mut self, pcr: u32, digest: &[u8; SHA256_DIGEST_SIZE]) -> DriverResult<()> {
+        if pcr >= TPM_PCR_COUNT {
+            return Err(DriverError::InvalidParameter);
+        }
+        self.execute(&build_pcr_extend(pcr, digest))?;
+        Ok(())
+    }
+
+    /// Read the SHA-256 value of every PCR in `mask`, in ascending order
+    pub fn pcr_read(&mut self, mask: u32) -> DriverResult<Vec<(u32, [u8; SHA256_DIGEST_SIZE])>> {
+        let mut values = Vec::new();
+        let mut remaining = mask & ((1 << TPM_PCR_COUNT) - 1);
+
+        // The TPM returns at most eight digests per call; ask again for the rest
+        while remaining != 0 {
+            let response = self.execute(&build_pcr_read(remaining))?;
+            let returned = parse_pcr_read(&response).ok_or(DriverError::InvalidData)?;
+            if returned.is_empty() {
+                break;
+            }
+            for (pcr, digest) in returned {
+                remaining &= !(1 << pcr);
+                values.push((pcr, digest));
+            }
+        }
+
+        values.sort_by_key(|(pcr, _)| *pcr);
+        Ok(values)
+    }
+
+    pub fn get_random(&mut self, out: &mut [u8]) -> DriverResult<()> {
+        let mut filled = 0;
+        while filled < out.len() {
+            let wanted = core::cmp::min(out.len() - filled, SHA256_DIGEST_SIZE) as u16;
+            let response = self.execute(&build_get_random(wanted))?;
+            let mut reader = ResponseReader::parse(&response).map_err(|_| DriverError::InvalidData)?;
+            let bytes = reader.get_tpm2b().ok_or(DriverError::InvalidData)?;
+            if bytes.is_empty() {
+                return Err(DriverError::NoData);
+            }
+            let take = core::cmp::min(bytes.len(), out.len() - filled);
+            out[filled..filled + take].copy_from_slice(&bytes[..take]);
+            filled += take;
+        }
+        Ok(())
+    }
+
+    fn create_primary(&mut self, hierarchy: u32, signing: bool) -> DriverResult<(u32, Vec<u8>)> {
+        let response = self.execute(&build_create_primary(hierarchy, signing))?;
+        let mut reader = ResponseReader::parse(&response).map_err(|_| DriverError::InvalidData)?;
+        let handle = reader.get_u32().ok_or(DriverError::InvalidData)?;
+        let _parameter_size = reader.get_u32().ok_or(DriverError::InvalidData)?;
+        let public = reader.get_tpm2b_raw().ok_or(DriverError::InvalidData)?;
+        Ok((handle, public.to_vec()))
+    }
+
+    /// Attestation key, created on first use in the endorsement hierarchy
+    fn attestation_key(&mut self) -> DriverResult<u32> {
+        if let Some(handle) = self.ak_handle {
+            return Ok(handle);
+        }
+        let (handle, public) = self.create_primary(TPM_RH_ENDORSEMENT, true)?;
+        self.ak_handle = Some(handle);
+        self.ak_public = public;
+        Ok(handle)
+    }
+
+    /// Storage root key, created on first use in the owner hierarchy
+    fn storage_root_key(&mut self) -> DriverResult<u32> {
+        if let Some(handle) = self.srk_handle {
+            return Ok(handle);
+        }
+        let (handle, _) = self.create_primary(TPM_RH_OWNER, false)?;
+        self.srk_handle = Some(handle);
+        Ok(handle)
+    }
+
+    pub fn ak_public(&self) -> &[u8] {
+        &self.ak_public
+    }
+
+    /// Response code of the last command the TPM rejected
+    pub fn last_error(&self) -> u32 {
+        self.last_error
+    }
+
+    pub fn quote(&mut self, nonce: &[u8], mask: u32) -> DriverResult<Quote> {
+        if nonce.len() > TPM_MAX_NONCE {
+            return Err(DriverError::InvalidParameter);
+        }
+        let ak = self.attestation_key()?;
+        let response = self.execute(&build_quote(ak, nonce, mask))?;
+
+        let mut reader = ResponseReader::parse(&response).map_err(|_| DriverError::InvalidData)?;
+        let _parameter_size = reader.get_u32().ok_or(DriverError::InvalidData)?;
+        let attest = reader.get_tpm2b().ok_or(DriverError::InvalidData)?.to_vec();
+        let signature = reader.remaining().to_vec();
+        Ok(Quote { attest, signature })
+    }
+
+    /// Seal `data` under the storage root key; the blob can be stored anywhere
+    pub fn seal(&mut self, data: &[u8]) -> DriverResult<Vec<u8>> {
+        if data.is_empty() || data.len() > TPM_MAX_SEALED_DATA {
+            return Err(DriverError::InvalidParameter);
+        }
+        let srk = self.storage_root_key()?;
+        let response = self.execute(&build_create_sealed(srk, data))?;
+
+        let mut reader = ResponseReader::parse(&response).map_err(|_| DriverError::InvalidData)?;
+        let _parameter_size = reader.get_u32().ok_or(DriverError::InvalidData)?;
+        let private = reader.get_tpm2b_raw().ok_or(DriverError::InvalidData)?;
+        let public = reader.get_tpm2b_raw().ok_or(DriverError::InvalidData)?;
+
+        let mut blob = Vec::with_capacity(private.len() + public.len());
+        blob.extend_from_slice(private);
+        blob.extend_from_slice(public);
+        Ok(blob)
+    }
+
+    pub fn unseal(&mut self, blob: &[u8]) -> DriverResult<Vec<u8>> {
+        let srk = self.storage_root_key()?;
+        let response = self.execute(&build_load(srk, blob))?;
+        let mut reader = ResponseReader::parse(&response).map_err(|_| DriverError::InvalidData)?;
+        let handle = reader.get_u32().ok_or(DriverError::InvalidData)?;
+
+        let result = self.execute(&build_unseal(handle)).and_then(|response| {
+            let mut reader = ResponseReader::parse(&response).map_err(|_| DriverError::InvalidData)?;
+            let _parameter_size = reader.get_u32().ok_or(DriverError::InvalidData)?;
+            Ok(reader.get_tpm2b().ok_or(DriverError::InvalidData)?.to_vec())
+        });
+
+        // Transient objects are a scarce resource; never leak one
+        let _ = self.execute(&build_flush_context(handle));
+        result
+    }
+}
+
+/// Extract `(pcr, digest)` pairs from a PCR_Read response
+pub fn parse_pcr_read(response: &[u8]) -> Option<Vec<(u32, [u8; SHA256_DIGEST_SIZE])>> {
+    let mut reader = ResponseReader::parse(response).ok()?;
+    let _update_counter = reader.get_u32()?;
+
+    // Selection actually returned, which determines the digest order
+    let mut pcrs = Vec::new();
+    let selections = reader.get_u32()?;
+    for _ in 0..selections {
+        let hash = reader.get_u16()?;
+        let size = reader.get_u8()? as usize;
+        let select = reader.get_bytes(size)?;
+        if hash != TPM_ALG_SHA256 {
+            continue;
+        }
+        for (byte_index, byte) in select.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (1 << bit) != 0 {
+                    pcrs.push((byte_index * 8 + bit) as u32);
+                }
+            }
+        }
+    }
+
+    let count = reader.get_u32()? as usize;
+    if count != pcrs.len() {
+        return None;
+    }
+
+    let mut values = Vec::with_capacity(count);
+    for pcr in pcrs {
+        let digest = reader.get_tpm2b()?;
+        if digest.len() != SHA256_DIGEST_SIZE {
+            return None;
+        }
+        let mut value = [0u8; SHA256_DIGEST_SIZE];
+        value.copy_from_slice(digest);
+        values.push((pcr, value));
+    }
+    Some(values)
+}
+
+// ========================================
+// ATTESTATION SERVICE
+// ========================================
+
+// Opcodes of the "tpm" endpoint
+const OP_PCR_READ: u32 = 1;
+const OP_QUOTE: u32 = 2;
+const OP_EVENT_LOG: u32 = 3;
+const OP_GET_RANDOM: u32 = 4;
+const OP_MEASURE: u32 = 5;
+const OP_SEAL: u32 = 6;
+const OP_UNSEAL: u32 = 7;
+
+// Reply status codes
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_EIO: i32 = -5;
+const STATUS_EINVAL: i32 = -22;
+
+/// PCR reserved for the kernel's own log; user space measures into 9 and up
+const MEASURE_PCR_KERNEL: u32 = 8;
+
+/// Entries returned by a single EVENT_LOG request
+const EVENT_LOG_BATCH: usize = 32;
+
+const MAX_RANDOM_REQUEST: usize = 256;
+
+struct TpmServer {
+    tpm: Tpm,
+    event_log: Vec<LogEntry>,
+    kernel_events_replayed: u32,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn put_blob(out: &mut Vec<u8>, blob: &[u8]) {
+    out.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+    out.extend_from_slice(blob);
+}
+
+impl TpmServer {
+    fn new(tpm: Tpm) -> Self {
+        let mut server = Self {
+            tpm,
+            event_log: Vec::new(),
+            kernel_events_replayed: 0,
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        };
+        server.replay_kernel_log();
+        server
+    }
+
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    /// Extend every kernel measurement not yet in the PCRs
+    fn replay_kernel_log(&mut self) {
+        let mut event = MeasureEvent::default();
+        while let Ok(total) = measure_log(self.kernel_events_replayed, &mut event) {
+            if self.tpm.pcr_extend(event.pcr, &event.digest).is_err() {
+                break;
+            }
+            let length = event.description.iter().position(|b| *b == 0).unwrap_or(event.description.len());
+            self.event_log.push(LogEntry {
+                pcr: event.pcr,
+                event_type: event.event_type,
+                digest: event.digest,
+                description: String::from_utf8_lossy(&event.description[..length]).into_owned(),
+            });
+            self.kernel_events_replayed += 1;
+            if self.kernel_events_replayed >= total {
+                break;
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let opcode = match read_u32(&message.data, 0) {
+            Some(opcode) => opcode,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+        let body = &message.data[4..];
+
+        // Reading state is open to any verifier; changing it or handling
+        // secrets requires the administrative right
+        let privileged = matches!(opcode, OP_MEASURE | OP_SEAL | OP_UNSEAL);
+        if privileged && !self.capabilities.check_rights(message.capability, CAP_ADMIN, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        // Attestation always reflects everything the kernel has measured
+        self.replay_kernel_log();
+
+        let mut payload = Vec::new();
+        let status = match opcode {
+            OP_PCR_READ => self.handle_pcr_read(body, &mut payload),
+            OP_QUOTE => self.handle_quote(body, &mut payload),
+            OP_EVENT_LOG => self.handle_event_log(body, &mut payload),
+            OP_GET_RANDOM => self.handle_get_random(body, &mut payload),
+            OP_MEASURE => self.handle_measure(body),
+            OP_SEAL => match self.tpm.seal(body) {
+                Ok(blob) => {
+                    payload = blob;
+                    STATUS_OK
+                }
+                Err(_) => STATUS_EIO,
+            },
+            OP_UNSEAL => match self.tpm.unseal(body) {
+                Ok(data) => {
+                    payload = data;
+                    STATUS_OK
+                }
+                Err(_) => STATUS_EIO,
+            },
+            _ => STATUS_EINVAL,
+        };
+        if status == STATUS_EIO {
+            payload = self.tpm.last_error().to_le_bytes().to_vec();
+        }
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+        wipe(&mut payload);
+    }
+
+    fn handle_pcr_read(&mut self, body: &[u8], out: &mut Vec<u8>) -> i32 {
+        let mask = match read_u32(body, 0) {
+            Some(mask) => mask,
+            None => return STATUS_EINVAL,
+        };
+        match self.tpm.pcr_read(mask) {
+            Ok(values) => {
+                out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+                for (pcr, digest) in values {
+                    out.extend_from_slice(&pcr.to_le_bytes());
+                    out.extend_from_slice(&digest);
+                }
+                STATUS_OK
+            }
+            Err(_) => STATUS_EIO,
+        }
+    }
+
+    /// Reply: attest, signature and attestation key public area, each
+    /// prefixed with a 32-bit length
+    fn handle_quote(&mut self, body: &[u8], out: &mut Vec<u8>) -> i32 {
+        let mask = match read_u32(body, 0) {
+            Some(mask) => mask,
+            None => return STATUS_EINVAL,
+        };
+        match self.tpm.quote(&body[4..], mask) {
+            Ok(quote) => {
+                put_blob(out, &quote.attest);
+                put_blob(out, &quote.signature);
+                put_blob(out, self.tpm.ak_public());
+                STATUS_OK
+            }
+            Err(DriverError::InvalidParameter) => STATUS_EINVAL,
+            Err(_) => STATUS_EIO,
+        }
+    }
+
+    /// Reply: total entries, entries returned, then pcr, type, digest and
+    /// length-prefixed description for each
+    fn handle_event_log(&mut self, body: &[u8], out: &mut Vec<u8>) -> i32 {
+        let start = match read_u32(body, 0) {
+            Some(start) => start as usize,
+            None => return STATUS_EINVAL,
+        };
+        let entries = self.event_log.iter().skip(start).take(EVENT_LOG_BATCH);
+
+        out.extend_from_slice(&(self.event_log.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(entries.clone().count() as u32).to_le_bytes());
+        for entry in entries {
+            out.extend_from_slice(&entry.pcr.to_le_bytes());
+            out.extend_from_slice(&entry.event_type.to_le_bytes());
+            out.extend_from_slice(&entry.digest);
+            out.extend_from_slice(&(entry.description.len() as u16).to_le_bytes());
+            out.extend_from_slice(entry.description.as_bytes());
+        }
+        STATUS_OK
+    }
+
+    fn handle_get_random(&mut self, body: &[u8], out: &mut Vec<u8>) -> i32 {
+        let length = match read_u32(body, 0) {
+            Some(length) => core::cmp::min(length as usize, MAX_RANDOM_REQUEST),
+            None => return STATUS_EINVAL,
+        };
+        out.resize(length, 0);
+        match self.tpm.get_random(out) {
+            Ok(()) => STATUS_OK,
+            Err(_) => {
+                out.clear();
+                STATUS_EIO
+            }
+        }
+    }
+
+    /// Record a measurement taken by a loader: pcr, type, digest, description
+    fn handle_measure(&mut self, body: &[u8]) -> i32 {
+        let (pcr, event_type) = match (read_u32(body, 0), read_u32(body, 4)) {
+            (Some(pcr), Some(event_type)) => (pcr, event_type),
+            _ => return STATUS_EINVAL,
+        };
+        if pcr <= MEASURE_PCR_KERNEL || pcr >= TPM_PCR_COUNT {
+            return STATUS_EINVAL;
+        }
+        let mut digest = [0u8; SHA256_DIGEST_SIZE];
+        match body.get(8..8 + SHA256_DIGEST_SIZE) {
+            Some(bytes) => digest.copy_from_slice(bytes),
+            None => return STATUS_EINVAL,
+        }
+        let description = String::from_utf8_lossy(&body[8 + SHA256_DIGEST_SIZE..]).into_owned();
+
+        if self.tpm.pcr_extend(pcr, &digest).is_err() {
+            return STATUS_EIO;
+        }
+        self.event_log.push(LogEntry { pcr, event_type, digest, description });
+        STATUS_OK
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    let tpm = match Tpm::probe() {
+        Ok(tpm) => tpm,
+        Err(_) => return,
+    };
+
+    let mut server = TpmServer::new(tpm);
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcr_extend_marshalling() {
+        let command = build_pcr_extend(9, &[0xAB; 32]);
+        // header + handle + auth area + count + hash alg + digest
+        assert_eq!(command.len(), 10 + 4 + 13 + 4 + 2 + 32);
+        assert_eq!(&command[0..2], &TPM_ST_SESSIONS.to_be_bytes());
+        assert_eq!(&command[2..6], &(command.len() as u32).to_be_bytes());
+        assert_eq!(&command[6..10], &TPM_CC_PCR_EXTEND.to_be_bytes());
+        assert_eq!(&command[10..14], &9u32.to_be_bytes());
+        assert_eq!(&command[18..22], &TPM_RS_PW.to_be_bytes());
+    }
+
+    #[test]
+    fn test_parse_pcr_read_response() {
+        // Responses share the command header layout, with the code in place
+        // of the command code
+        let mut response = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_RC_SUCCESS);
+        response.put_u32(42); // update counter
+        response.put_pcr_selection((1 << 0) | (1 << 9));
+        response.put_u32(2);
+        response.put_tpm2b(&[0x11; 32]);
+        response.put_tpm2b(&[0x99; 32]);
+
+        let values = parse_pcr_read(&response.finish()).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0], (0, [0x11; 32]));
+        assert_eq!(values[1], (9, [0x99; 32]));
+    }
+
+    #[test]
+    fn test_error_response_is_rejected() {
+        let response = CommandBuffer::new(TPM_ST_NO_SESSIONS, TPM_RC_INITIALIZE).finish();
+        assert_eq!(ResponseReader::parse(&response).err(), Some(TPM_RC_INITIALIZE));
+    }
+}
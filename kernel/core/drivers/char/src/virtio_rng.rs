@@ -0,0 +1,386 @@
+/*
+ * Orion Operating System - VirtIO Entropy Driver
+ *
+ * Driver for the VirtIO entropy device (virtio-rng). Keeps device-writable
+ * buffers posted on the request queue and forwards every filled buffer to
+ * the entropy server, which credits it as hardware entropy.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+use orion_driver::{
+    DeviceInfo, DriverError, DriverInfo, DriverResult, OrionDriver,
+    MmioAccessor, MmioPermissions, MessageLoop, ReceivedMessage,
+    virtio_constants::*,
+};
+use orion_crypto::wipe;
+use orion_ipc::IpcChannel;
+
+// VirtIO entropy device identifiers
+const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
+const VIRTIO_RNG_DEVICE_ID_LEGACY: u16 = 0x1005;
+const VIRTIO_RNG_DEVICE_ID_MODERN: u16 = 0x1044;
+const VIRTIO_ID_RNG: u32 = 4;
+
+// Descriptor flags
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+
+// Request queue geometry
+const RNG_QUEUE_INDEX: u32 = 0;
+const RNG_BUFFER_SIZE: usize = 64;
+const RNG_MAX_BUFFERS: usize = 16;
+
+// Entropy server protocol (see services/entropy/src/protocol.rs)
+const ENTROPY_OP_ADD_ENTROPY: u32 = 2;
+const ENTROPY_SOURCE_VIRTIO_RNG: u32 = 0;
+
+#[repr(C, packed)]
+struct VirtqDesc {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C, packed)]
+struct VirtqAvail {
+    flags: u16,
+    idx: u16,
+    ring: [u16; 0],
+}
+
+#[repr(C, packed)]
+struct VirtqUsedElem {
+    id: u32,
+    len: u32,
+}
+
+#[repr(C, packed)]
+struct VirtqUsed {
+    flags: u16,
+    idx: u16,
+    ring: [VirtqUsedElem; 0],
+}
+
+/// Request queue; each descriptor permanently owns one entropy buffer
+struct RngQueue {
+    desc: *mut VirtqDesc,
+    avail: *mut VirtqAvail,
+    used: *mut VirtqUsed,
+    size: u16,
+    last_used_idx: u16,
+}
+
+impl RngQueue {
+    fn new(memory: *mut u8, size: u16) -> Self {
+        unsafe {
+            let desc = memory as *mut VirtqDesc;
+            let avail_offset = core::mem::size_of::<VirtqDesc>() * size as usize;
+            let avail = memory.add(avail_offset) as *mut VirtqAvail;
+            let used_offset = (avail_offset + 4 + 2 * size as usize + 0xFFF) & !0xFFF;
+            let used = memory.add(used_offset) as *mut VirtqUsed;
+
+            (*avail).idx = 0;
+            (*used).idx = 0;
+
+            RngQueue { desc, avail, used, size, last_used_idx: 0 }
+        }
+    }
+
+    /// Hand descriptor `index`, pointing at `buffer`, to the device
+    fn post(&mut self, index: u16, buffer: *mut u8, len: usize) {
+        unsafe {
+            let desc = &mut *self.desc.add(index as usize);
+            desc.addr = buffer as u64;
+            desc.len = len as u32;
+            desc.flags = VIRTQ_DESC_F_WRITE;
+            desc.next = 0;
+
+            let avail_idx = (*self.avail).idx;
+            let ring = core::slice::from_raw_parts_mut((*self.avail).ring.as_mut_ptr(), self.size as usize);
+            ring[(avail_idx % self.size) as usize] = index;
+            core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+            (*self.avail).idx = avail_idx.wrapping_add(1);
+        }
+    }
+
+    /// Pop the next completed descriptor and the number of bytes written
+    fn pop_used(&mut self) -> Option<(u16, usize)> {
+        unsafe {
+            core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+            if (*self.used).idx == self.last_used_idx {
+                return None;
+            }
+            let ring = core::slice::from_raw_parts((*self.used).ring.as_ptr(), self.size as usize);
+            let elem = &ring[(self.last_used_idx % self.size) as usize];
+            let id = elem.id as u16;
+            let len = elem.len as usize;
+            self.last_used_idx = self.last_used_idx.wrapping_add(1);
+            Some((id, len))
+        }
+    }
+}
+
+/// VirtIO entropy device driver
+pub struct VirtioRngDriver {
+    device: DeviceInfo,
+    mmio: MmioAccessor,
+    queue: Option<RngQueue>,
+    buffers: [[u8; RNG_BUFFER_SIZE]; RNG_MAX_BUFFERS],
+    buffer_count: u16,
+    entropy_channel: IpcChannel,
+    bytes_harvested: u64,
+    bytes_forwarded: u64,
+}
+
+impl OrionDriver for VirtioRngDriver {
+    fn probe(device: &DeviceInfo) -> DriverResult<bool> {
+        Ok(device.vendor_id == VIRTIO_VENDOR_ID
+            && (device.device_id == VIRTIO_RNG_DEVICE_ID_LEGACY
+                || device.device_id == VIRTIO_RNG_DEVICE_ID_MODERN))
+    }
+
+    fn init(device: DeviceInfo) -> DriverResult<Self> {
+        let mmio = unsafe {
+            MmioAccessor::new(
+                device.bars[0],
+                4096,
+                MmioPermissions::READ | MmioPermissions::WRITE | MmioPermissions::UNCACHED
+            )
+        };
+
+        if mmio.read_u32(VIRTIO_MMIO_MAGIC_VALUE)? != 0x74726976 {
+            return Err(DriverError::DeviceNotFound);
+        }
+        if mmio.read_u32(VIRTIO_MMIO_DEVICE_ID)? != VIRTIO_ID_RNG {
+            return Err(DriverError::DeviceNotFound);
+        }
+
+        let mut driver = VirtioRngDriver {
+            device,
+            mmio,
+            queue: None,
+            buffers: [[0; RNG_BUFFER_SIZE]; RNG_MAX_BUFFERS],
+            buffer_count: 0,
+            entropy_channel: IpcChannel::connect("entropy"),
+            bytes_harvested: 0,
+            bytes_forwarded: 0,
+        };
+        driver.initialize_device()?;
+
+        Ok(driver)
+    }
+
+    fn handle_irq(&mut self) -> DriverResult<()> {
+        let status = self.mmio.read_u32(VIRTIO_MMIO_INTERRUPT_STATUS)?;
+
+        if status & 1 != 0 {
+            self.harvest()?;
+        }
+
+        self.mmio.write_u32(VIRTIO_MMIO_INTERRUPT_ACK, status)?;
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> DriverResult<()> {
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, 0)?;
+        self.queue = None;
+        Ok(())
+    }
+
+    fn info(&self) -> DriverInfo {
+        DriverInfo {
+            name: "VirtIO Entropy Driver",
+            version: "1.0.0",
+            author: "Jeremy Noverraz",
+            description: "VirtIO hardware random number generator feeding the entropy server",
+        }
+    }
+}
+
+impl VirtioRngDriver {
+    fn initialize_device(&mut self) -> DriverResult<()> {
+        // Reset, then ACKNOWLEDGE | DRIVER
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, 0)?;
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, VIRTIO_STATUS_ACKNOWLEDGE)?;
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER)?;
+
+        // virtio-rng defines no device-specific feature bits
+        self.mmio.write_u32(VIRTIO_MMIO_DRIVER_FEATURES, 0)?;
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS,
+            VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER | VIRTIO_STATUS_FEATURES_OK)?;
+        if self.mmio.read_u32(VIRTIO_MMIO_STATUS)? & VIRTIO_STATUS_FEATURES_OK == 0 {
+            return Err(DriverError::InitializationFailed);
+        }
+
+        // Set up the single request queue
+        self.mmio.write_u32(VIRTIO_MMIO_QUEUE_SEL, RNG_QUEUE_INDEX)?;
+        let max_size = self.mmio.read_u32(VIRTIO_MMIO_QUEUE_NUM_MAX)? as usize;
+        if max_size == 0 {
+            return Err(DriverError::InitializationFailed);
+        }
+        let size = core::cmp::min(max_size, RNG_MAX_BUFFERS) as u16;
+
+        let memory = Self::allocate_queue_memory()?;
+        self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NUM, size as u32)?;
+        self.mmio.write_u64(0x040, memory as u64)?;
+        self.mmio.write_u32(VIRTIO_MMIO_QUEUE_READY, 1)?;
+
+        let mut queue = RngQueue::new(memory, size);
+        for index in 0..size {
+            let buffer = self.buffers[index as usize].as_mut_ptr();
+            queue.post(index, buffer, RNG_BUFFER_SIZE);
+        }
+        self.queue = Some(queue);
+        self.buffer_count = size;
+
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS,
+            VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER |
+            VIRTIO_STATUS_FEATURES_OK | VIRTIO_STATUS_DRIVER_OK)?;
+
+        self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, RNG_QUEUE_INDEX)?;
+        Ok(())
+    }
+
+    /// Forward every completed buffer to the entropy server and repost it
+    fn harvest(&mut self) -> DriverResult<()> {
+        let mut reposted = false;
+
+        loop {
+            let (index, len) = match self.queue.as_mut().ok_or(DriverError::DeviceNotReady)?.pop_used() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if index >= self.buffer_count {
+                return Err(DriverError::General);
+            }
+
+            let len = core::cmp::min(len, RNG_BUFFER_SIZE);
+            if len > 0 {
+                self.forward(index as usize, len);
+            }
+
+            // Scrub before handing the buffer back so no copy lingers in memory
+            let buffer = &mut self.buffers[index as usize];
+            wipe(buffer);
+            let buffer_ptr = buffer.as_mut_ptr();
+            if let Some(queue) = self.queue.as_mut() {
+                queue.post(index, buffer_ptr, RNG_BUFFER_SIZE);
+            }
+            reposted = true;
+        }
+
+        if reposted {
+            self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, RNG_QUEUE_INDEX)?;
+        }
+        Ok(())
+    }
+
+    fn forward(&mut self, index: usize, len: usize) {
+        let mut message = [0u8; 12 + RNG_BUFFER_SIZE];
+        message[0..4].copy_from_slice(&ENTROPY_OP_ADD_ENTROPY.to_le_bytes());
+        message[4..8].copy_from_slice(&ENTROPY_SOURCE_VIRTIO_RNG.to_le_bytes());
+        message[8..12].copy_from_slice(&((len * 8) as u32).to_le_bytes());
+        message[12..12 + len].copy_from_slice(&self.buffers[index][..len]);
+
+        self.bytes_harvested += len as u64;
+        if self.entropy_channel.post(&message[..12 + len]).is_ok() {
+            self.bytes_forwarded += len as u64;
+        }
+
+        wipe(&mut message);
+    }
+
+    fn allocate_queue_memory() -> DriverResult<*mut u8> {
+        // Two pages: descriptor table + available ring, then the used ring
+        #[repr(C, align(4096))]
+        struct QueuePages([u8; 8192]);
+        static mut QUEUE_MEMORY: QueuePages = QueuePages([0; 8192]);
+        static mut QUEUE_ALLOCATED: bool = false;
+
+        unsafe {
+            if QUEUE_ALLOCATED {
+                return Err(DriverError::MemoryError);
+            }
+            QUEUE_ALLOCATED = true;
+            Ok(QUEUE_MEMORY.0.as_mut_ptr())
+        }
+    }
+}
+
+/// Driver entry point
+#[no_mangle]
+pub extern "C" fn driver_main() {
+    let mut message_loop = match MessageLoop::new() {
+        Ok(loop_obj) => loop_obj,
+        Err(_) => return,
+    };
+
+    static mut DRIVER: Option<VirtioRngDriver> = None;
+
+    let _ = message_loop.run(
+        "virtio-rng",
+        "1.0.0",
+        &[VIRTIO_VENDOR_ID],
+        &[VIRTIO_RNG_DEVICE_ID_LEGACY, VIRTIO_RNG_DEVICE_ID_MODERN],
+        |ipc, message| {
+            match message {
+                ReceivedMessage::ProbeDevice(probe_msg) => {
+                    let device = DeviceInfo::new(probe_msg.vendor_id, probe_msg.device_id, orion_driver::BusType::Virtual);
+                    let can_handle = VirtioRngDriver::probe(&device).unwrap_or(false);
+                    ipc.send_probe_response(probe_msg.header.sequence, can_handle)
+                }
+
+                ReceivedMessage::InitDevice(device_handle) => {
+                    let mut device = DeviceInfo::new(VIRTIO_VENDOR_ID, VIRTIO_RNG_DEVICE_ID_MODERN, orion_driver::BusType::Virtual);
+                    device.bars[0] = device_handle as u64;
+                    match VirtioRngDriver::init(device) {
+                        Ok(driver) => {
+                            unsafe { DRIVER = Some(driver) };
+                            ipc.send_io_response(0, Ok(0))
+                        }
+                        Err(e) => ipc.send_io_response(0, Err(e)),
+                    }
+                }
+
+                ReceivedMessage::IoRequest(io_msg) => {
+                    // The device is consumed through the entropy server only
+                    ipc.send_io_response(io_msg.header.sequence, Err(DriverError::Unsupported))
+                }
+
+                ReceivedMessage::Interrupt(_device_handle) => {
+                    let result = match unsafe { DRIVER.as_mut() } {
+                        Some(driver) => driver.handle_irq().map(|_| 0),
+                        None => Err(DriverError::DeviceNotReady),
+                    };
+                    ipc.send_io_response(0, result)
+                }
+
+                ReceivedMessage::Shutdown => {
+                    if let Some(driver) = unsafe { DRIVER.as_mut() } {
+                        let _ = driver.shutdown();
+                    }
+                    Ok(())
+                }
+
+                ReceivedMessage::Unknown => Ok(()),
+            }
+        },
+    );
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
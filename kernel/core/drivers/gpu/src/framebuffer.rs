@@ -5,6 +5,10 @@
  * graphics operations, and performance optimization. Supports various pixel formats
  * and provides a complete graphics API for the Orion OS.
  *
+ * Drives the linear framebuffer the boot loader set up (SYS_BOOT_FRAMEBUFFER):
+ * its address, resolution and pixel layout come from the boot information, so
+ * the driver only starts when the loader left one behind.
+ *
  * Features:
  * - Multiple display modes and resolutions
  * - Hardware acceleration support
@@ -42,6 +46,7 @@ use core::{
     sync::atomic::{AtomicU64, AtomicU32, Ordering},
     fmt,
 };
+use orion_sys::{boot_framebuffer, BootFramebuffer};
 
 // ========================================
 // FRAMEBUFFER DRIVER STRUCTURES
@@ -50,6 +55,7 @@ use core::{
 /// Main framebuffer driver structure
 pub struct FramebufferDriver {
     device_info: DeviceInfo,
+    boot_display: Option<BootDisplay>,
     state: DriverState,
     stats: FramebufferStats,
     display_manager: DisplayManager,
@@ -128,6 +134,15 @@ pub enum PixelFormat {
     Grayscale8,
 }
 
+/// Framebuffer left by the boot loader
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootDisplay {
+    address: u64,
+    size: u64,
+    pitch: u32,
+    mode: DisplayMode,
+}
+
 /// Display capabilities
 #[derive(Debug, Clone)]
 pub struct DisplayCapabilities {
@@ -368,6 +383,7 @@ impl OrionDriver for FramebufferDriver {
     fn init(device: DeviceInfo) -> DriverResult<Self> {
         let mut driver = FramebufferDriver {
             device_info: device,
+            boot_display: BootDisplay::query(),
             state: DriverState::Initializing,
             stats: FramebufferStats::new(),
             display_manager: DisplayManager::new(),
@@ -505,7 +521,7 @@ impl FramebufferDriver {
         self.graphics_manager.initialize()?;
         
         // Initialize memory manager
-        self.memory_manager.initialize()?;
+        self.memory_manager.initialize(self.boot_display.as_ref())?;
         
         // Initialize performance monitor
         self.performance_monitor.initialize()?;
@@ -521,14 +537,16 @@ impl FramebufferDriver {
     
     /// Initialize device-specific features
     fn initialize_device(&mut self) -> DriverResult<()> {
-        // Detect display capabilities
-        self.display_manager.detect_displays()?;
+        let boot_display = self.boot_display.clone().ok_or(DriverError::DeviceNotFound)?;
         
         // Set default display mode
-        self.display_manager.set_default_mode()?;
+        self.display_manager.set_default_mode(&boot_display)?;
+        
+        // Detect display capabilities
+        self.display_manager.detect_displays()?;
         
-        // Allocate framebuffer memory
-        self.memory_manager.allocate_framebuffer()?;
+        // Account for the framebuffer memory
+        self.memory_manager.allocate_framebuffer(&boot_display)?;
         
         // Initialize graphics pipeline
         self.graphics_manager.initialize_pipeline()?;
@@ -633,14 +651,9 @@ impl FramebufferDriver {
     
     /// Set display mode
     fn set_display_mode(&mut self, io_msg: &orion_driver::IoMessage) -> DriverResult<usize> {
-        // Parse display mode parameters from io_msg.data
-        self.display_manager.set_mode(DisplayMode {
-            width: 1024,
-            height: 768,
-            refresh_rate: 60,
-            pixel_format: PixelFormat::RGB888,
-            depth: 24,
-        })?;
+        // The boot framebuffer cannot change mode; only its own is accepted
+        let boot_display = self.boot_display.as_ref().ok_or(DriverError::DeviceNotFound)?;
+        self.display_manager.set_mode(boot_display.mode.clone())?;
         Ok(0)
     }
     
@@ -698,6 +711,43 @@ impl FramebufferStats {
     }
 }
 
+impl BootDisplay {
+    /// Framebuffer the loader handed over, if any
+    fn query() -> Option<Self> {
+        let mut framebuffer = BootFramebuffer::default();
+        boot_framebuffer(&mut framebuffer).ok()?;
+        Self::from_framebuffer(&framebuffer)
+    }
+    
+    fn from_framebuffer(framebuffer: &BootFramebuffer) -> Option<Self> {
+        if framebuffer.address == 0 || framebuffer.width == 0 || framebuffer.height == 0 {
+            return None;
+        }
+        let pixel_format = match framebuffer.bpp {
+            32 if framebuffer.red_shift == 16 => PixelFormat::ARGB8888,
+            32 => PixelFormat::RGBA8888,
+            24 if framebuffer.red_shift == 16 => PixelFormat::RGB888,
+            24 => PixelFormat::BGR888,
+            16 if framebuffer.green_size == 6 => PixelFormat::RGB565,
+            16 => PixelFormat::RGB555,
+            _ => return None,
+        };
+        let depth = framebuffer.red_size + framebuffer.green_size + framebuffer.blue_size;
+        Some(Self {
+            address: framebuffer.address,
+            size: framebuffer.pitch as u64 * framebuffer.height as u64,
+            pitch: framebuffer.pitch,
+            mode: DisplayMode {
+                width: framebuffer.width,
+                height: framebuffer.height,
+                refresh_rate: 60,
+                pixel_format,
+                depth,
+            },
+        })
+    }
+}
+
 impl DisplayManager {
     fn new() -> Self {
         Self {
@@ -756,13 +806,13 @@ impl DisplayManager {
     }
     
     fn detect_displays(&mut self) -> DriverResult<()> {
-        // Detect connected displays
+        // The boot framebuffer is the only display
         let display = DisplayInfo {
             id: 0,
             name: "Primary Display".to_string(),
             capabilities: DisplayCapabilities {
-                max_width: 1920,
-                max_height: 1080,
+                max_width: self.current_mode.width,
+                max_height: self.current_mode.height,
                 max_refresh_rate: 144,
                 supports_vsync: true,
                 supports_hardware_acceleration: true,
@@ -775,15 +825,12 @@ impl DisplayManager {
         Ok(())
     }
     
-    fn set_default_mode(&mut self) -> DriverResult<()> {
-        // Set default display mode
-        self.current_mode = DisplayMode {
-            width: 1024,
-            height: 768,
-            refresh_rate: 60,
-            pixel_format: PixelFormat::RGB888,
-            depth: 24,
-        };
+    fn set_default_mode(&mut self, boot_display: &BootDisplay) -> DriverResult<()> {
+        // Keep the mode the loader set
+        self.current_mode = boot_display.mode.clone();
+        if !self.supported_modes.contains(&self.current_mode) {
+            self.supported_modes.push(self.current_mode.clone());
+        }
         Ok(())
     }
     
@@ -845,16 +892,18 @@ impl MemoryManager {
         }
     }
     
-    fn initialize(&mut self) -> DriverResult<()> {
-        // Initialize memory pools
-        let framebuffer_pool = MemoryPool {
-            id: 0,
-            base_address: 0x80000000,
-            size: 32 * 1024 * 1024, // 32MB for framebuffer
-            used: 0,
-            pool_type: MemoryPoolType::Framebuffer,
-        };
-        self.memory_pools.insert(0, framebuffer_pool);
+    fn initialize(&mut self, boot_display: Option<&BootDisplay>) -> DriverResult<()> {
+        // Initialize memory pools, the framebuffer pool being the boot framebuffer
+        if let Some(boot_display) = boot_display {
+            let framebuffer_pool = MemoryPool {
+                id: 0,
+                base_address: boot_display.address,
+                size: boot_display.size,
+                used: 0,
+                pool_type: MemoryPoolType::Framebuffer,
+            };
+            self.memory_pools.insert(0, framebuffer_pool);
+        }
         
         let texture_pool = MemoryPool {
             id: 1,
@@ -876,17 +925,20 @@ impl MemoryManager {
         Ok(())
     }
     
-    fn allocate_framebuffer(&mut self) -> DriverResult<()> {
-        // Allocate framebuffer memory
+    fn allocate_framebuffer(&mut self, boot_display: &BootDisplay) -> DriverResult<()> {
+        // The whole boot framebuffer is in use from the start
         let allocation = MemoryAllocation {
-            address: 0x80000000,
-            size: 1024 * 768 * 4, // 1024x768 32-bit
+            address: boot_display.address,
+            size: boot_display.size,
             pool_id: 0,
             allocation_type: AllocationType::Static,
             alignment: 4096,
         };
-        self.allocations.insert(0x80000000, allocation);
-        self.used_memory += 1024 * 768 * 4;
+        self.allocations.insert(boot_display.address, allocation);
+        self.used_memory += boot_display.size;
+        if let Some(pool) = self.memory_pools.get_mut(&0) {
+            pool.used = boot_display.size;
+        }
         Ok(())
     }
     
@@ -994,6 +1046,12 @@ impl DebugManager {
 /// Driver entry point
 #[no_mangle]
 pub extern "C" fn driver_main() {
+    // Nothing to drive unless the loader left a framebuffer
+    let boot_display = match BootDisplay::query() {
+        Some(boot_display) => boot_display,
+        None => return,
+    };
+    
     // Create message loop for kernel communication
     let mut message_loop = match MessageLoop::new() {
         Ok(loop_obj) => loop_obj,
@@ -1014,7 +1072,7 @@ pub extern "C" fn driver_main() {
                 device_class: 0x03,
                 device_subclass: 0x00,
                 device_protocol: 0x00,
-                bars: vec![0x80000000], // Framebuffer base address
+                bars: vec![boot_display.address], // Framebuffer base address
             }) {
                 Ok(d) => d,
                 Err(_) => return Err(DriverError::InitializationFailed),
@@ -1074,6 +1132,44 @@ mod tests {
         assert!(format1 < format2);
     }
     
+    fn boot_framebuffer_32bpp() -> BootFramebuffer {
+        BootFramebuffer {
+            address: 0xFD000000,
+            width: 1280,
+            height: 800,
+            pitch: 5120,
+            bpp: 32,
+            red_size: 8,
+            red_shift: 16,
+            green_size: 8,
+            green_shift: 8,
+            blue_size: 8,
+            blue_shift: 0,
+        }
+    }
+    
+    #[test]
+    fn test_boot_display_from_framebuffer() {
+        let display = BootDisplay::from_framebuffer(&boot_framebuffer_32bpp()).unwrap();
+        assert_eq!(display.address, 0xFD000000);
+        assert_eq!(display.size, 5120 * 800);
+        assert_eq!(display.mode.width, 1280);
+        assert_eq!(display.mode.pixel_format, PixelFormat::ARGB8888);
+        assert_eq!(display.mode.depth, 24);
+        
+        let mut rgb565 = boot_framebuffer_32bpp();
+        rgb565.bpp = 16;
+        rgb565.red_size = 5;
+        rgb565.green_size = 6;
+        rgb565.blue_size = 5;
+        let display = BootDisplay::from_framebuffer(&rgb565).unwrap();
+        assert_eq!(display.mode.pixel_format, PixelFormat::RGB565);
+        
+        let mut missing = boot_framebuffer_32bpp();
+        missing.address = 0;
+        assert!(BootDisplay::from_framebuffer(&missing).is_none());
+    }
+    
     #[test]
     fn test_driver_state_transitions() {
         let mut driver = FramebufferDriver {
@@ -1083,8 +1179,9 @@ mod tests {
                 device_class: 0x03,
                 device_subclass: 0x00,
                 device_protocol: 0x00,
-                bars: vec![0x80000000],
+                bars: vec![0xFD000000],
             },
+            boot_display: BootDisplay::from_framebuffer(&boot_framebuffer_32bpp()),
             state: DriverState::Uninitialized,
             stats: FramebufferStats::new(),
             display_manager: DisplayManager::new(),
@@ -59,11 +59,33 @@ use alloc::{
     vec::Vec,
     collections::BTreeMap,
     boxed::Box,
+    rc::Rc,
 };
 use core::{
-    sync::atomic::{AtomicU64, AtomicU32, Ordering},
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicU32, Ordering},
     fmt,
 };
+use orion_frameperf::{FrameMonitor, QueueOccupancy, FRAME_IOCTL_CONTROL};
+use orion_ipc::IpcChannel;
+use orion_sys::clock_get;
+use orion_thermal::ThermalSensor;
+use orion_virtq::{DescChain, DescTable, DmaAllocator, DmaPool, DmaRegion, LeakTracker, VirtqDesc, VIRTQ_DESC_F_WRITE};
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// Thermal server channel, where the GPU reports its temperature
+struct ThermalIpc(IpcChannel);
+
+impl orion_thermal::Transport for ThermalIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
 
 // VirtIO MMIO constants
 const VIRTIO_MMIO_MAGIC_VALUE: usize = 0x000;
@@ -71,7 +93,9 @@ const VIRTIO_MMIO_VERSION: usize = 0x004;
 const VIRTIO_MMIO_DEVICE_ID: usize = 0x008;
 const VIRTIO_MMIO_VENDOR_ID: usize = 0x00c;
 const VIRTIO_MMIO_DEVICE_FEATURES: usize = 0x010;
+const VIRTIO_MMIO_DEVICE_FEATURES_SEL: usize = 0x014;
 const VIRTIO_MMIO_DRIVER_FEATURES: usize = 0x020;
+const VIRTIO_MMIO_DRIVER_FEATURES_SEL: usize = 0x024;
 const VIRTIO_MMIO_QUEUE_SEL: usize = 0x030;
 const VIRTIO_MMIO_QUEUE_NUM_MAX: usize = 0x034;
 const VIRTIO_MMIO_QUEUE_NUM: usize = 0x038;
@@ -80,6 +104,11 @@ const VIRTIO_MMIO_QUEUE_NOTIFY: usize = 0x050;
 const VIRTIO_MMIO_INTERRUPT_STATUS: usize = 0x060;
 const VIRTIO_MMIO_INTERRUPT_ACK: usize = 0x064;
 const VIRTIO_MMIO_STATUS: usize = 0x070;
+const VIRTIO_MMIO_SHM_SEL: usize = 0x0ac;
+const VIRTIO_MMIO_SHM_LEN_LOW: usize = 0x0b0;
+const VIRTIO_MMIO_SHM_LEN_HIGH: usize = 0x0b4;
+const VIRTIO_MMIO_SHM_BASE_LOW: usize = 0x0b8;
+const VIRTIO_MMIO_SHM_BASE_HIGH: usize = 0x0bc;
 const VIRTIO_MMIO_CONFIG: usize = 0x100;
 
 // VirtIO status constants
@@ -92,10 +121,12 @@ const VIRTIO_STATUS_FEATURES_OK: u32 = 8;
 const VIRTIO_GPU_DEVICE_ID: u32 = 0x10;
 
 // VirtIO GPU device features
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
 const VIRTIO_GPU_F_VIRGL: u64 = 1 << 0;        // 3D acceleration support
 const VIRTIO_GPU_F_EDID: u64 = 1 << 1;         // EDID support
 const VIRTIO_GPU_F_RESOURCE_UUID: u64 = 1 << 2; // Resource UUID support
 const VIRTIO_GPU_F_RESOURCE_BLOB: u64 = 1 << 3; // Resource blob support
+const VIRTIO_GPU_F_CONTEXT_INIT: u64 = 1 << 4; // Context types (capsets) support
 
 // VirtIO MMIO interrupt constants
 const VIRTIO_MMIO_INT_VRING: u32 = 0x1;
@@ -107,6 +138,8 @@ const VIRTIO_GPU_RESP_OK_DISPLAY_INFO: u32 = 0x1101;
 const VIRTIO_GPU_RESP_OK_CAPSET_INFO: u32 = 0x1102;
 const VIRTIO_GPU_RESP_OK_CAPSET: u32 = 0x1103;
 const VIRTIO_GPU_RESP_OK_EDID: u32 = 0x1104;
+const VIRTIO_GPU_RESP_OK_RESOURCE_UUID: u32 = 0x1105;
+const VIRTIO_GPU_RESP_OK_MAP_INFO: u32 = 0x1106;
 
 // VirtIO GPU error responses
 const VIRTIO_GPU_RESP_ERR_UNSPEC: u32 = 0x1200;
@@ -126,6 +159,23 @@ const VIRTIO_GPU_CMD_TRANSFER_TO_HOST_3D: u32 = 0x0205;
 const VIRTIO_GPU_CMD_TRANSFER_FROM_HOST_3D: u32 = 0x0206;
 const VIRTIO_GPU_CMD_SUBMIT_3D: u32 = 0x0207;
 
+// Compute contexts: virgl command stream carried by SUBMIT_3D
+const VIRTIO_GPU_CONTEXT_INIT_CAPSET_ID_MASK: u32 = 0xff;
+const VIRTIO_GPU_CAPSET_VIRGL2: u32 = 2;
+const VIRGL_CCMD_CREATE_OBJECT: u32 = 1;
+const VIRGL_CCMD_BIND_SHADER: u32 = 31;
+const VIRGL_CCMD_SET_SHADER_BUFFERS: u32 = 34;
+const VIRGL_CCMD_MEMORY_BARRIER: u32 = 36;
+const VIRGL_CCMD_LAUNCH_GRID: u32 = 37;
+const VIRGL_OBJECT_SHADER: u32 = 4;
+const PIPE_SHADER_COMPUTE: u32 = 5;
+const PIPE_BUFFER: u32 = 0;
+const PIPE_BARRIER_MAPPED_BUFFER: u32 = 1 << 0;
+const PIPE_BARRIER_SHADER_BUFFER: u32 = 1 << 1;
+const VIRGL_FORMAT_R8_UNORM: u32 = 64;
+const VIRGL_BIND_SHADER_BUFFER: u32 = 1 << 14;
+const VIRGL_MAX_SHADER_BUFFERS: usize = 16;
+
 // Cursor commands
 const VIRTIO_GPU_CMD_UPDATE_CURSOR: u32 = 0x0300;
 const VIRTIO_GPU_CMD_MOVE_CURSOR: u32 = 0x0301;
@@ -137,6 +187,55 @@ const VIRTIO_GPU_CMD_RESOURCE_DETACH_BACKING: u32 = 0x0107;
 const VIRTIO_GPU_CMD_GET_CAPSET_INFO: u32 = 0x0108;
 const VIRTIO_GPU_CMD_GET_CAPSET: u32 = 0x0109;
 const VIRTIO_GPU_CMD_GET_EDID: u32 = 0x010a;
+const VIRTIO_GPU_CMD_RESOURCE_ASSIGN_UUID: u32 = 0x010b;
+const VIRTIO_GPU_CMD_RESOURCE_CREATE_BLOB: u32 = 0x010c;
+const VIRTIO_GPU_CMD_RESOURCE_MAP_BLOB: u32 = 0x0208;
+const VIRTIO_GPU_CMD_RESOURCE_UNMAP_BLOB: u32 = 0x0209;
+
+// Blob resources: memory types, usage flags and host mapping cache types
+const VIRTIO_GPU_BLOB_MEM_GUEST: u32 = 0x0001;
+const VIRTIO_GPU_BLOB_MEM_HOST3D: u32 = 0x0002;
+const VIRTIO_GPU_BLOB_MEM_HOST3D_GUEST: u32 = 0x0003;
+const VIRTIO_GPU_BLOB_FLAG_USE_MAPPABLE: u32 = 0x0001;
+const VIRTIO_GPU_BLOB_FLAG_USE_SHAREABLE: u32 = 0x0002;
+const VIRTIO_GPU_BLOB_FLAG_USE_CROSS_DEVICE: u32 = 0x0004;
+const VIRTIO_GPU_MAP_CACHE_MASK: u32 = 0x0f;
+const VIRTIO_GPU_MAP_CACHE_CACHED: u32 = 0x01;
+const VIRTIO_GPU_MAP_CACHE_UNCACHED: u32 = 0x02;
+const VIRTIO_GPU_MAP_CACHE_WC: u32 = 0x03;
+const VIRTIO_GPU_SHM_ID_HOST_VISIBLE: u32 = 1;
+const VIRTIO_GPU_BLOB_PAGE_SIZE: u64 = 4096;
+
+// Ioctl selecting a power mode; data[0] is one of the POWER_MODE_* values
+pub const GPU_IOCTL_POWER_MODE: u32 = 0x04;
+pub const POWER_MODE_PERFORMANCE: u8 = 0;
+pub const POWER_MODE_BALANCED: u8 = 1;
+pub const POWER_MODE_POWER_SAVING: u8 = 2;
+
+// Ioctl logging the descriptor chains and DMA buffers still held
+pub const GPU_IOCTL_LEAK_REPORT: u32 = 0x05;
+
+// Frame statistics are queried with FRAME_IOCTL_CONTROL, the reply sent
+// back as data
+
+/// Frame time aimed at until the display says otherwise, 60 Hz
+const DEFAULT_FRAME_TARGET_NS: u64 = 16_666_667;
+
+// GPU memory pools (id, type, window), below the command arena
+const POOL_SYSTEM: u32 = 0;
+const POOL_FRAMEBUFFER: u32 = 1;
+const POOL_RESOURCE: u32 = 2;
+const MEMORY_POOLS: [(u32, MemoryPoolType, u64, u64); 3] = [
+    (POOL_SYSTEM, MemoryPoolType::System, 0x1000000, 0x1000000),
+    (POOL_FRAMEBUFFER, MemoryPoolType::Video, 0x2000000, 0x2000000),
+    (POOL_RESOURCE, MemoryPoolType::Unified, 0x4000000, 0x4000000),
+];
+const GPU_MEMORY_ALIGN: usize = 4096;
+
+// Window the per-command buffers are taken from
+const COMMAND_ARENA_BASE: u64 = 0x8000000;
+const COMMAND_ARENA_SIZE: u64 = 0x1000000;
+const COMMAND_BUFFER_ALIGN: usize = 64;
 
 // VirtIO GPU commands
 const VIRTIO_GPU_CMD_GET_DISPLAY_INFO: u32 = 0x0100;
@@ -156,6 +255,9 @@ const VIRTIO_GPU_FORMAT_X8B8G8R8_UNORM: u32 = 68;
 const VIRTIO_GPU_FORMAT_A8B8G8R8_UNORM: u32 = 121;
 const VIRTIO_GPU_FORMAT_R8G8B8X8_UNORM: u32 = 134;
 
+// Resource the scanout shows, backed by the framebuffer
+const FRAMEBUFFER_RESOURCE_ID: u32 = 1;
+
 // VirtIO GPU limits
 const VIRTIO_GPU_MAX_SCANOUTS: usize = 16;
 const VIRTIO_GPU_MAX_RESOURCES: usize = 256;
@@ -292,16 +394,6 @@ struct VirtioGpuMoveCursor {
     padding: [u8; 4],
 }
 
-/// VirtIO descriptor structure
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-struct VirtioDesc {
-    addr: u64,      // Physical address
-    len: u32,       // Length
-    flags: u16,     // Flags
-    next: u16,      // Next descriptor index
-}
-
 /// VirtIO available ring structure
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -331,98 +423,71 @@ struct VirtioUsedElem {
 }
 
 /// VirtIO queue structure
+///
+/// Descriptors are handed out as chains that go back to the table when
+/// dropped, so a command abandoned on a timeout or error cannot leak them.
 struct VirtioQueue {
     queue_id: u16,
     size: usize,
-    desc: *mut VirtioDesc,
+    desc: DescTable,
     avail: *mut VirtioAvail,
     used: *mut VirtioUsed,
-    free_head: u16,
-    num_free: u16,
-    last_used_idx: u16,
-    desc_free: [u16; VIRTIO_QUEUE_SIZE],
+    last_used_idx: Cell<u16>,
+    /// Commands outstanding over time, for the GPU utilization estimate
+    occupancy: Rc<QueueOccupancy>,
 }
 
 impl VirtioQueue {
     /// Create a new VirtIO queue
-    unsafe fn new(queue_id: u16, size: usize, desc_addr: u64, avail_addr: u64, used_addr: u64) -> Self {
-        let desc = desc_addr as *mut VirtioDesc;
-        let avail = avail_addr as *mut VirtioAvail;
-        let used = used_addr as *mut VirtioUsed;
-        
-        // Initialize descriptor free list
-        let mut desc_free = [0u16; VIRTIO_QUEUE_SIZE];
-        for i in 0..size {
-            desc_free[i] = i as u16;
-        }
-        
+    unsafe fn new(
+        queue_id: u16,
+        size: usize,
+        desc_addr: u64,
+        avail_addr: u64,
+        used_addr: u64,
+        leaks: Rc<LeakTracker>,
+        occupancy: Rc<QueueOccupancy>,
+    ) -> Self {
         Self {
             queue_id,
             size,
-            desc,
-            avail,
-            used,
-            free_head: 0,
-            num_free: size as u16,
-            last_used_idx: 0,
-            desc_free,
+            desc: DescTable::new(queue_id, desc_addr as *mut VirtqDesc, size as u16, leaks),
+            avail: avail_addr as *mut VirtioAvail,
+            used: used_addr as *mut VirtioUsed,
+            last_used_idx: Cell::new(0),
+            occupancy,
         }
     }
     
-    /// Allocate a descriptor chain
-    unsafe fn alloc_desc(&mut self, num: usize) -> Option<u16> {
-        if self.num_free < num as u16 {
-            return None;
-        }
-        
-        let head = self.free_head;
-        let mut last = head;
-        
-        for _ in 1..num {
-            let next = self.desc_free[last as usize];
-            (*self.desc.offset(last as isize)).next = next;
-            last = next;
-        }
-        
-        (*self.desc.offset(last as isize)).next = 0;
-        self.free_head = self.desc_free[last as usize];
-        self.num_free -= num as u16;
-        
-        Some(head)
-    }
-    
-    /// Free a descriptor chain
-    unsafe fn free_desc(&mut self, head: u16, num: usize) {
-        let mut next = head;
-        for _ in 0..num {
-            let desc = &mut *self.desc.offset(next as isize);
-            next = desc.next;
-            self.desc_free[self.num_free as usize] = next;
-            self.num_free += 1;
-        }
-        self.free_head = head;
+    /// Allocate a descriptor chain, freed when dropped
+    #[track_caller]
+    fn alloc_chain(&self, num: u16) -> Option<DescChain<'_>> {
+        self.desc.alloc_chain(num)
     }
     
-    /// Add descriptor to available ring
-    unsafe fn add_to_avail(&mut self, head: u16) {
+    /// Add descriptor chain to available ring
+    unsafe fn add_to_avail(&self, chain: &DescChain<'_>) {
         let avail = &mut *self.avail;
         let idx = avail.idx as usize % self.size;
-        avail.ring[idx] = head;
+        avail.ring[idx] = chain.head();
         avail.idx = avail.idx.wrapping_add(1);
+        self.occupancy.submitted(monotonic_ns());
     }
     
     /// Check for completed requests
-    unsafe fn check_used(&mut self) -> Option<u16> {
+    unsafe fn check_used(&self) -> Option<u16> {
         let used = &*self.used;
-        if used.idx == self.last_used_idx {
+        let last_used_idx = self.last_used_idx.get();
+        if used.idx == last_used_idx {
             return None;
         }
         
-        let idx = self.last_used_idx as usize % self.size;
+        let idx = last_used_idx as usize % self.size;
         let elem = used.ring[idx];
-        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+        self.last_used_idx.set(last_used_idx.wrapping_add(1));
+        self.occupancy.completed(monotonic_ns());
         
-        Some(elem.id)
+        Some(elem.id as u16)
     }
 }
 
@@ -444,7 +509,12 @@ pub struct VirtioGpuDriver {
     control_queue: Option<VirtioQueue>,
     cursor_queue: Option<VirtioQueue>,
     queue_memory: Option<*mut u8>,
+    leaks: Rc<LeakTracker>,
+    command_pool: DmaPool<DmaRegion>,
+    resource_refs: Rc<RefCell<ResourceRefs>>,
     supports_3d: bool,
+    features: u64,
+    host_visible: Option<HostVisibleRegion>,
     num_scanouts: u32,
     current_scanout: u32,
     last_config_generation: u32,
@@ -465,16 +535,17 @@ pub enum DriverState {
     ShuttingDown,
 }
 
-/// VirtIO GPU driver statistics
-#[derive(Debug)]
-pub struct VirtioGpuStats {
-    commands_processed: AtomicU64,
-    frames_rendered: AtomicU64,
-    bytes_transferred: AtomicU64,
-    interrupts_handled: AtomicU64,
-    errors_encountered: AtomicU64,
-    last_command_time: AtomicU64,
-    performance_metrics: AtomicU64,
+orion_stats::counter_set! {
+    /// VirtIO GPU driver statistics, kept per CPU
+    pub struct VirtioGpuStats => VirtioGpuStatsValues {
+        commands_processed,
+        frames_rendered,
+        bytes_transferred,
+        interrupts_handled,
+        errors_encountered,
+        last_command_time,
+        performance_metrics,
+    }
 }
 
 /// Display manager for handling multiple displays
@@ -490,19 +561,26 @@ pub struct GraphicsManager {
     contexts: BTreeMap<u32, ContextInfo>,
     render_targets: BTreeMap<u32, RenderTarget>,
     shaders: BTreeMap<u32, ShaderInfo>,
+    blobs: BTreeMap<u32, BlobResource>,
+    uuids: BTreeMap<u32, [u8; 16]>,
 }
 
-/// Memory manager for GPU memory allocation
+/// Memory manager for GPU memory allocation: one first-fit free list per
+/// pool, so freed memory is reused and neighbouring free ranges merge
 pub struct MemoryManager {
     memory_pools: BTreeMap<u32, MemoryPool>,
     allocations: BTreeMap<u64, MemoryAllocation>,
     total_memory: u64,
     used_memory: u64,
+    /// Bumped on every allocation or use, to order eviction
+    clock: u64,
 }
 
 /// Performance monitor for optimization
 pub struct PerformanceMonitor {
-    frame_times: Vec<u64>,
+    /// Frame times, janks and control queue occupancy, read through
+    /// FRAME_IOCTL_CONTROL
+    frames: FrameMonitor,
     command_latencies: Vec<u64>,
     memory_usage: Vec<u64>,
     gpu_utilization: AtomicU32,
@@ -513,6 +591,9 @@ pub struct PowerManager {
     current_power_state: PowerState,
     power_modes: BTreeMap<String, PowerMode>,
     thermal_monitor: ThermalMonitor,
+    /// Zone of the thermal server fed from the thermal monitor
+    thermal_sensor: ThermalSensor,
+    thermal_channel: Option<ThermalIpc>,
 }
 
 /// Debug manager for development and troubleshooting
@@ -635,6 +716,86 @@ pub enum ResourceType {
     VertexBuffer,
     IndexBuffer,
     UniformBuffer,
+    StorageBuffer,
+    Blob,
+}
+
+/// Reference counts of the resources handed out as handles, shared with
+/// the handles. A resource whose last handle goes away is queued here and
+/// destroyed by the driver on its next resource operation
+#[derive(Default)]
+struct ResourceRefs {
+    /// Serial of the resource's current incarnation and its handle count
+    counts: BTreeMap<u32, (u64, u32)>,
+    orphaned: Vec<u32>,
+    next_serial: u64,
+}
+
+impl ResourceRefs {
+    fn register(&mut self, id: u32) -> u64 {
+        self.next_serial += 1;
+        self.counts.insert(id, (self.next_serial, 1));
+        self.next_serial
+    }
+
+    fn is_held(&self, id: u32) -> bool {
+        self.counts.contains_key(&id)
+    }
+
+    fn forget(&mut self, id: u32) {
+        self.counts.remove(&id);
+        self.orphaned.retain(|&orphan| orphan != id);
+    }
+}
+
+/// Counted reference to a resource; the resource is destroyed once every
+/// clone of its handle has been dropped
+pub struct ResourceHandle {
+    id: u32,
+    serial: u64,
+    refs: Rc<RefCell<ResourceRefs>>,
+}
+
+impl ResourceHandle {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Clone for ResourceHandle {
+    fn clone(&self) -> Self {
+        if let Some((serial, count)) = self.refs.borrow_mut().counts.get_mut(&self.id) {
+            if *serial == self.serial {
+                *count += 1;
+            }
+        }
+        Self { id: self.id, serial: self.serial, refs: self.refs.clone() }
+    }
+}
+
+impl Drop for ResourceHandle {
+    fn drop(&mut self) {
+        let mut refs = self.refs.borrow_mut();
+        // A resource destroyed some other way, or a later one with its id,
+        // is none of this handle's business
+        let last = match refs.counts.get_mut(&self.id) {
+            Some((serial, count)) if *serial == self.serial => {
+                *count -= 1;
+                *count == 0
+            }
+            _ => false,
+        };
+        if last {
+            refs.counts.remove(&self.id);
+            refs.orphaned.push(self.id);
+        }
+    }
+}
+
+impl fmt::Debug for ResourceHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ResourceHandle").field(&self.id).finish()
+    }
 }
 
 /// Context information structure
@@ -687,13 +848,27 @@ pub enum ShaderType {
 }
 
 /// Memory pool structure
-#[derive(Debug, Clone)]
 pub struct MemoryPool {
     id: u32,
     pool_type: MemoryPoolType,
     base_address: u64,
     size: u64,
     used: u64,
+    free_list: DmaRegion,
+}
+
+/// Occupancy of one pool. Fragmentation is the share of free memory
+/// outside the largest free range: 0 when all of it is in one piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryPoolStats {
+    pub pool_id: u32,
+    pub pool_type: MemoryPoolType,
+    pub size: u64,
+    pub used: u64,
+    pub free: u64,
+    pub largest_free: u64,
+    pub free_ranges: usize,
+    pub fragmentation_percent: u32,
 }
 
 /// Memory pool type enumeration
@@ -713,6 +888,12 @@ pub struct MemoryAllocation {
     size: usize,
     allocation_type: AllocationType,
     pool_id: u32,
+    /// Resource the memory backs, if any
+    owner: Option<u32>,
+    /// The owner only caches content that can be recreated, and may be
+    /// destroyed to make room
+    evictable: bool,
+    last_used: u64,
 }
 
 /// Allocation type enumeration
@@ -939,6 +1120,8 @@ impl GraphicsManager {
             contexts: BTreeMap::new(),
             render_targets: BTreeMap::new(),
             shaders: BTreeMap::new(),
+            blobs: BTreeMap::new(),
+            uuids: BTreeMap::new(),
         }
     }
 
@@ -948,6 +1131,8 @@ impl GraphicsManager {
         self.contexts.clear();
         self.render_targets.clear();
         self.shaders.clear();
+        self.blobs.clear();
+        self.uuids.clear();
         Ok(())
     }
 
@@ -964,76 +1149,153 @@ impl GraphicsManager {
 
 impl MemoryManager {
     pub fn new() -> Self {
-        Self {
+        let mut manager = Self {
             memory_pools: BTreeMap::new(),
             allocations: BTreeMap::new(),
             total_memory: 0,
             used_memory: 0,
-        }
+            clock: 0,
+        };
+        manager.reset_pools();
+        manager
     }
 
     pub fn initialize(&mut self) -> DriverResult<()> {
-        // Initialize memory manager
+        self.reset_pools();
+        Ok(())
+    }
+
+    // Every pool empty again, forgetting all allocations
+    fn reset_pools(&mut self) {
         self.memory_pools.clear();
         self.allocations.clear();
-        self.total_memory = 0;
+        for (id, pool_type, base_address, size) in MEMORY_POOLS {
+            self.memory_pools.insert(id, MemoryPool {
+                id,
+                pool_type,
+                base_address,
+                size,
+                used: 0,
+                free_list: DmaRegion::new(base_address, size),
+            });
+        }
+        self.total_memory = MEMORY_POOLS.iter().map(|pool| pool.3).sum();
         self.used_memory = 0;
-        Ok(())
     }
 
-    pub fn allocate_memory(&mut self, size: usize, pool_type: MemoryPoolType) -> DriverResult<u64> {
-        // Simulate memory allocation
-        let address = 0x1000000 + self.used_memory;
+    /// Allocate `size` bytes, page aligned, from a pool; OutOfMemory when
+    /// no free range of the pool is large enough
+    pub fn allocate(&mut self, pool_id: u32, size: usize, allocation_type: AllocationType) -> DriverResult<u64> {
+        if size == 0 {
+            return Err(DriverError::InvalidParameter);
+        }
+        let size = size.div_ceil(GPU_MEMORY_ALIGN) * GPU_MEMORY_ALIGN;
+        let pool = self.memory_pools.get_mut(&pool_id).ok_or(DriverError::InvalidParameter)?;
+        let address = pool.free_list.alloc(size, GPU_MEMORY_ALIGN).ok_or(DriverError::OutOfMemory)?;
+        pool.used += size as u64;
         self.used_memory += size as u64;
-        
-        let allocation = MemoryAllocation {
+        self.clock += 1;
+        self.allocations.insert(address, MemoryAllocation {
             address,
             size,
-            allocation_type: AllocationType::Temporary,
-            pool_id: 0,
-        };
-        
-        self.allocations.insert(address, allocation);
+            allocation_type,
+            pool_id,
+            owner: None,
+            evictable: false,
+            last_used: self.clock,
+        });
         Ok(address)
     }
+
+    /// Give an allocation back to its pool
+    pub fn free(&mut self, address: u64) -> DriverResult<()> {
+        let allocation = self.allocations.remove(&address).ok_or(DriverError::InvalidParameter)?;
+        if let Some(pool) = self.memory_pools.get_mut(&allocation.pool_id) {
+            pool.free_list.free(address, allocation.size);
+            pool.used -= allocation.size as u64;
+        }
+        self.used_memory -= allocation.size as u64;
+        Ok(())
+    }
+
+    pub fn allocate_memory(&mut self, size: usize, pool_type: MemoryPoolType) -> DriverResult<u64> {
+        let pool_id = if pool_type == MemoryPoolType::Video { POOL_FRAMEBUFFER } else { POOL_SYSTEM };
+        self.allocate(pool_id, size, AllocationType::Temporary)
+    }
     
     pub fn allocate_framebuffer(&mut self, size: usize) -> DriverResult<u64> {
-        // Allocate memory specifically for framebuffer
-        let address = 0x2000000 + self.used_memory;
-        self.used_memory += size as u64;
-        
-        let allocation = MemoryAllocation {
-            address,
-            size,
-            allocation_type: AllocationType::Framebuffer,
-            pool_id: 1,
-        };
-        
-        self.allocations.insert(address, allocation);
-        Ok(address)
+        self.allocate(POOL_FRAMEBUFFER, size, AllocationType::Framebuffer)
     }
     
     pub fn allocate_resource(&mut self, size: usize) -> DriverResult<u64> {
-        // Allocate memory for GPU resources
-        let address = 0x3000000 + self.used_memory;
-        self.used_memory += size as u64;
-        
-        let allocation = MemoryAllocation {
-            address,
-            size,
-            allocation_type: AllocationType::Resource,
-            pool_id: 2,
-        };
-        
-        self.allocations.insert(address, allocation);
-        Ok(address)
+        self.allocate(POOL_RESOURCE, size, AllocationType::Resource)
+    }
+
+    /// Record the resource an allocation backs
+    pub fn set_owner(&mut self, address: u64, resource_id: u32) {
+        if let Some(allocation) = self.allocations.get_mut(&address) {
+            allocation.owner = Some(resource_id);
+        }
+    }
+
+    pub fn set_evictable(&mut self, address: u64, evictable: bool) -> DriverResult<()> {
+        let allocation = self.allocations.get_mut(&address).ok_or(DriverError::InvalidParameter)?;
+        allocation.evictable = evictable;
+        Ok(())
+    }
+
+    /// Note a use of the allocation, making it the last to be evicted
+    pub fn touch(&mut self, address: u64) {
+        self.clock += 1;
+        if let Some(allocation) = self.allocations.get_mut(&address) {
+            allocation.last_used = self.clock;
+        }
+    }
+
+    /// Least recently used evictable resource of a pool
+    pub fn eviction_candidate(&self, pool_id: u32) -> Option<u32> {
+        self.least_recently_used(|allocation| allocation.pool_id == pool_id)
+    }
+
+    /// Least recently used evictable resource of any pool
+    pub fn any_eviction_candidate(&self) -> Option<u32> {
+        self.least_recently_used(|_| true)
+    }
+
+    fn least_recently_used(&self, filter: impl Fn(&MemoryAllocation) -> bool) -> Option<u32> {
+        self.allocations
+            .values()
+            .filter(|allocation| allocation.evictable && filter(allocation))
+            .filter_map(|allocation| allocation.owner.map(|owner| (allocation.last_used, owner)))
+            .min()
+            .map(|(_, owner)| owner)
+    }
+
+    pub fn pool_stats(&self, pool_id: u32) -> Option<MemoryPoolStats> {
+        let pool = self.memory_pools.get(&pool_id)?;
+        let free = pool.free_list.free_bytes();
+        let largest_free = pool.free_list.largest_free();
+        Some(MemoryPoolStats {
+            pool_id: pool.id,
+            pool_type: pool.pool_type,
+            size: pool.size,
+            used: pool.used,
+            free,
+            largest_free,
+            free_ranges: pool.free_list.free_ranges(),
+            fragmentation_percent: ((free - largest_free) * 100).checked_div(free).unwrap_or(0) as u32,
+        })
+    }
+
+    pub fn all_pool_stats(&self) -> Vec<MemoryPoolStats> {
+        self.memory_pools.keys().filter_map(|&id| self.pool_stats(id)).collect()
     }
 }
 
 impl PerformanceMonitor {
     pub fn new() -> Self {
         Self {
-            frame_times: Vec::new(),
+            frames: FrameMonitor::new(DEFAULT_FRAME_TARGET_NS),
             command_latencies: Vec::new(),
             memory_usage: Vec::new(),
             gpu_utilization: AtomicU32::new(0),
@@ -1042,19 +1304,29 @@ impl PerformanceMonitor {
 
     pub fn initialize(&mut self) -> DriverResult<()> {
         // Initialize performance monitor
-        self.frame_times.clear();
+        self.frames.reset();
         self.command_latencies.clear();
         self.memory_usage.clear();
         self.gpu_utilization.store(0, Ordering::Relaxed);
         Ok(())
     }
 
-    pub fn record_frame_time(&mut self, frame_time: u64) {
-        self.frame_times.push(frame_time);
-        if self.frame_times.len() > 100 {
-            self.frame_times.remove(0);
+    /// A frame reached the screen
+    pub fn frame_presented(&mut self, now_ns: u64) {
+        self.frames.present(now_ns);
+    }
+
+    /// Frame time aimed at, from the refresh rate of the display
+    pub fn set_refresh_rate(&mut self, refresh_hz: u32) {
+        if refresh_hz > 0 {
+            self.frames.set_target(1_000_000_000 / refresh_hz as u64);
         }
     }
+
+    /// Serve a frame performance request (see orion_frameperf)
+    pub fn handle_control(&mut self, request: &[u8]) -> Vec<u8> {
+        orion_frameperf::handle_control(&mut self.frames, request, monotonic_ns())
+    }
 }
 
 impl PowerManager {
@@ -1067,7 +1339,20 @@ impl PowerManager {
                 critical_temperature: 85,
                 thermal_zones: vec![0, 1, 2],
             },
+            // Critical trip at the monitor's critical temperature
+            thermal_sensor: ThermalSensor::new("gpu", 0, 85_000),
+            thermal_channel: None,
+        }
+    }
+
+    /// Forward the monitored temperature to the thermal server
+    pub fn report_temperature(&mut self, now_ns: u64) {
+        let temp_mc = self.thermal_monitor.current_temperature as i32 * 1000;
+        if !self.thermal_sensor.due(temp_mc, now_ns) {
+            return;
         }
+        let channel = self.thermal_channel.get_or_insert_with(|| ThermalIpc(IpcChannel::connect("thermal")));
+        self.thermal_sensor.report(channel, temp_mc, now_ns);
     }
 
     pub fn initialize(&mut self) -> DriverResult<()> {
@@ -1204,6 +1489,7 @@ impl OrionDriver for VirtioGpuDriver {
             },
         };
         
+        performance_monitor.set_refresh_rate(default_display.refresh_rate);
         display_manager.add_display(default_display)?;
         
         // Initialize VirtIO queues (simplified for now)
@@ -1211,18 +1497,19 @@ impl OrionDriver for VirtioGpuDriver {
         let cursor_queue = None;  // Will be initialized in real implementation
         let queue_memory = None;  // Will be allocated in real implementation
         
+        // Descriptor chains and command buffers are tracked together so that
+        // a leak report covers both
+        let leaks = Rc::new(LeakTracker::new("virtio-gpu"));
+        let command_pool = DmaPool::new(
+            DmaRegion::new(COMMAND_ARENA_BASE, COMMAND_ARENA_SIZE),
+            COMMAND_BUFFER_ALIGN,
+            leaks.clone(),
+        );
+        
         Ok(VirtioGpuDriver {
             device_info: device,
             state: DriverState::Ready,
-            stats: VirtioGpuStats {
-                commands_processed: AtomicU64::new(0),
-                frames_rendered: AtomicU64::new(0),
-                bytes_transferred: AtomicU64::new(0),
-                interrupts_handled: AtomicU64::new(0),
-                errors_encountered: AtomicU64::new(0),
-                last_command_time: AtomicU64::new(0),
-                performance_metrics: AtomicU64::new(0),
-            },
+            stats: VirtioGpuStats::new(),
             display_manager,
             graphics_manager,
             memory_manager,
@@ -1232,7 +1519,12 @@ impl OrionDriver for VirtioGpuDriver {
             control_queue,
             cursor_queue,
             queue_memory,
+            leaks,
+            command_pool,
+            resource_refs: Rc::default(),
             supports_3d: true, // Default to 3D support
+            features: 0,
+            host_visible: None,
             num_scanouts: 1, // Default to single scanout
             current_scanout: 0,
             last_config_generation: 0,
@@ -1244,7 +1536,7 @@ impl OrionDriver for VirtioGpuDriver {
     
     fn handle_irq(&mut self) -> DriverResult<()> {
         // Update statistics
-        self.stats.interrupts_handled.fetch_add(1, Ordering::Relaxed);
+        self.stats.interrupts_handled().inc();
         
         // Handle VirtIO GPU interrupts
         // Read MMIO registers to check interrupt status
@@ -1266,9 +1558,10 @@ impl OrionDriver for VirtioGpuDriver {
         Ok(())
     }
     
-    fn handle_message(&mut self, message: &ReceivedMessage) -> DriverResult<()> {
+    fn handle_message(&mut self, message: &ReceivedMessage, ipc: &mut dyn IpcInterface) -> DriverResult<()> {
         // Update statistics
-        self.stats.commands_processed.fetch_add(1, Ordering::Relaxed);
+        self.stats.commands_processed().inc();
+        self.power_manager.report_temperature(monotonic_ns());
         
         match message {
             ReceivedMessage::ProbeDevice(probe_msg) => {
@@ -1281,8 +1574,17 @@ impl OrionDriver for VirtioGpuDriver {
             ReceivedMessage::InitDevice(init_msg) => {
                 // Handle device initialization
                 // This would typically involve setting up VirtIO queues
+                self.negotiate_features()?;
                 self.state = DriverState::Active;
             }
+            ReceivedMessage::IoRequest(io_msg)
+                if matches!(io_msg.request_type, orion_driver::IoRequestType::Ioctl)
+                    && io_msg.command == FRAME_IOCTL_CONTROL =>
+            {
+                // Frame statistics answer with data rather than a status
+                let reply = self.performance_monitor.handle_control(io_msg.data.as_deref().unwrap_or(&[]));
+                return ipc.send_response(io_msg.header.sequence, 0, &reply);
+            }
             ReceivedMessage::IoRequest(io_msg) => {
                 // Handle I/O requests
                 self.handle_gpu_ioctl(io_msg)?;
@@ -1317,10 +1619,15 @@ impl OrionDriver for VirtioGpuDriver {
         // Update state
         self.state = DriverState::ShuttingDown;
         
-        // Clean up resources
+        // Clean up resources; handles still out now count on a table the
+        // driver no longer looks at
         self.graphics_manager.resources.clear();
         self.graphics_manager.contexts.clear();
-        self.memory_manager.allocations.clear();
+        self.memory_manager.reset_pools();
+        self.resource_refs = Rc::default();
+        
+        // Anything still held by now was never given back
+        self.report_leaks();
         
         // Reset state
         self.state = DriverState::Uninitialized;
@@ -1376,7 +1683,7 @@ impl GraphicsDriver for VirtioGpuDriver {
         self.state = DriverState::Active;
         
         // Update statistics
-        self.stats.frames_rendered.fetch_add(1, Ordering::Relaxed);
+        self.stats.frames_rendered().inc();
         
         Ok(())
     }
@@ -1417,7 +1724,7 @@ impl GraphicsDriver for VirtioGpuDriver {
                 }
                 
                 // Update statistics
-                self.stats.bytes_transferred.fetch_add(4, Ordering::Relaxed);
+                self.stats.bytes_transferred().add(4);
             }
         }
             
@@ -1434,7 +1741,7 @@ impl GraphicsDriver for VirtioGpuDriver {
             let bytes_cleared = pixel_count * 4;
             
             // Update statistics
-            self.stats.bytes_transferred.fetch_add(bytes_cleared, Ordering::Relaxed);
+            self.stats.bytes_transferred().add(bytes_cleared);
             
             Ok(())
         } else {
@@ -1452,7 +1759,7 @@ impl GraphicsDriver for VirtioGpuDriver {
             }
             
             // Update statistics
-            self.stats.bytes_transferred.fetch_add(buffer.len() as u64, Ordering::Relaxed);
+            self.stats.bytes_transferred().add(buffer.len() as u64);
             
             Ok(())
         } else {
@@ -1510,124 +1817,995 @@ impl VirtioGpuDriver {
                             self.set_scanout(scanout_id, resource_id, x, y, width, height)?;
                         }
                     }
+                    GPU_IOCTL_POWER_MODE => { // Set power mode (power policy service dims the display)
+                        let mode = match io_msg.data.as_ref().and_then(|data| data.first()) {
+                            Some(&POWER_MODE_PERFORMANCE) => "Performance",
+                            Some(&POWER_MODE_BALANCED) => "Balanced",
+                            Some(&POWER_MODE_POWER_SAVING) => "PowerSaving",
+                            _ => return Err(DriverError::InvalidParameter),
+                        };
+                        self.set_power_mode(mode)?;
+                    }
+                    GPU_IOCTL_LEAK_REPORT => { // Log outstanding queue handles
+                        self.report_leaks();
+                    }
                     _ => return Err(DriverError::Unsupported),
                 }
             }
         }
         
         // Update statistics
-        self.stats.commands_processed.fetch_add(1, Ordering::Relaxed);
+        self.stats.commands_processed().inc();
         
         Ok(())
     }
     
+    /// Log descriptor chains and command buffers taken and not given back,
+    /// with where they were taken; nothing is tracked in release builds
+    /// unless enabled
+    fn report_leaks(&mut self) {
+        let report = self.leaks.report();
+        if !report.is_empty() {
+            self.debug_manager.log_error_message(&format!("{}", report));
+        }
+    }
+    
     /// Handle queue interrupts
     fn handle_queue_interrupt(&mut self) -> DriverResult<()> {
         // Process VirtIO queue completions
         if let Some(ref mut control_queue) = &mut self.control_queue {
             // Check for completed descriptors in control queue
             while let Some(completed_id) = control_queue.check_used() {
-                // Process completed command
-                let desc = unsafe { &*control_queue.desc.offset(completed_id as isize) };
-                
-                // Free the descriptor
-                control_queue.free_desc(completed_id, 1);
+                // Process completed command; its descriptors are given
+                // back by the chain the submitter holds
                 
                 // Update statistics
-                self.stats.commands_processed.fetch_add(1, Ordering::Relaxed);
+                self.stats.commands_processed().inc();
             }
         }
         
         if let Some(ref mut cursor_queue) = &mut self.cursor_queue {
             // Check for completed descriptors in cursor queue
             while let Some(completed_id) = cursor_queue.check_used() {
-                // Process completed cursor command
-                let desc = unsafe { &*cursor_queue.desc.offset(completed_id as isize) };
-                
-                // Free the descriptor
-                cursor_queue.free_desc(completed_id, 1);
+                // Process completed cursor command; its descriptors are given
+                // back by the chain the submitter holds
                 
                 // Update statistics
-                self.stats.commands_processed.fetch_add(1, Ordering::Relaxed);
+                self.stats.commands_processed().inc();
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Handle configuration changes
+    fn handle_config_change(&mut self) -> DriverResult<()> {
+        // Handle display hotplug, resolution changes, and other config events
+        let config_generation = self.mmio.read_u32(VIRTIO_MMIO_CONFIG + 4)?;
+        
+        if config_generation != self.last_config_generation {
+            // Configuration has changed, update display information
+            self.get_display_info()?;
+            
+            // Check for new scanouts
+            let num_scanouts = self.mmio.read_u32(VIRTIO_MMIO_CONFIG + 8)?;
+            if num_scanouts != self.num_scanouts {
+                self.num_scanouts = num_scanouts;
+                
+                // Update display manager with new scanout information
+                for i in 0..self.num_scanouts {
+                    let scanout_info = self.get_scanout_info(i)?;
+                    self.display_manager.update_scanout(i, scanout_info);
+                }
+            }
+            
+            // Update last known configuration generation
+            self.last_config_generation = config_generation;
+            
+            // Notify any registered configuration change handlers
+            self.handle_display_hotplug()?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Get driver statistics
+    pub fn get_statistics(&self) -> &VirtioGpuStats {
+        &self.stats
+    }
+    
+    /// Get current driver state
+    pub fn get_state(&self) -> DriverState {
+        self.state
+    }
+    
+    /// Set power mode
+    pub fn set_power_mode(&mut self, mode_name: &str) -> DriverResult<()> {
+        if let Some(mode) = self.power_manager.power_modes.get(mode_name) {
+            // Apply power mode settings
+            self.power_manager.set_power_state(PowerState::Active)?;
+            Ok(())
+        } else {
+            Err(DriverError::InvalidParameter)
+        }
+    }
+    
+    /// Get display information
+    pub fn get_display_info(&self, display_id: u32) -> Option<&DisplayInfo> {
+        self.display_manager.get_display(display_id)
+    }
+    
+    /// Create a new resource, destroyed when the last clone of the
+    /// returned handle is dropped
+    pub fn create_resource(&mut self, resource_type: ResourceType, width: u32, height: u32, format: PixelFormat) -> DriverResult<ResourceHandle> {
+        self.reserve_resource_slot()?;
+        let resource_id = self.next_resource_id();
+        
+        let resource = ResourceInfo {
+            id: resource_id,
+            resource_type,
+            width,
+            height,
+            format,
+            memory_address: 0,
+            memory_size: (width * height * 4) as usize,
+        };
+        
+        self.graphics_manager.create_resource(resource)?;
+        Ok(self.resource_handle(resource_id))
+    }
+}
+
+// ========================================
+// GPU MEMORY
+// ========================================
+
+impl VirtioGpuDriver {
+    /// Allocate backing memory from a pool. A full pool first loses its
+    /// cached resources, least recently used first, until the allocation
+    /// fits; OutOfMemory once none is left
+    fn allocate_gpu_memory(&mut self, pool_id: u32, size: usize, allocation_type: AllocationType) -> DriverResult<u64> {
+        loop {
+            match self.memory_manager.allocate(pool_id, size, allocation_type) {
+                Err(DriverError::OutOfMemory) => {
+                    let victim = self.memory_manager.eviction_candidate(pool_id).ok_or(DriverError::OutOfMemory)?;
+                    self.evict_resource(victim)?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Destroy a resource wherever it is in use: detached from every
+    /// context first, blobs unmapped
+    fn evict_resource(&mut self, resource_id: u32) -> DriverResult<()> {
+        if self.graphics_manager.blobs.contains_key(&resource_id) {
+            return self.destroy_blob_resource(resource_id);
+        }
+        let holders: Vec<u32> = self
+            .graphics_manager
+            .contexts
+            .values()
+            .filter(|context| context.active_resources.contains(&resource_id))
+            .map(|context| context.id)
+            .collect();
+        for ctx_id in holders {
+            self.detach_from_context(ctx_id, resource_id)?;
+        }
+        self.release_resource(resource_id)
+    }
+
+    fn detach_from_context(&mut self, ctx_id: u32, resource_id: u32) -> DriverResult<()> {
+        let mut cmd = control_header(VIRTIO_GPU_CMD_CTX_DETACH_RESOURCE, ctx_id);
+        cmd.extend_from_slice(&resource_id.to_le_bytes());
+        cmd.extend_from_slice(&0u32.to_le_bytes());
+        self.submit_control_nodata(&cmd)?;
+        if let Some(context) = self.graphics_manager.contexts.get_mut(&ctx_id) {
+            context.active_resources.retain(|&id| id != resource_id);
+        }
+        Ok(())
+    }
+
+    // Unreference a resource on the host and free its backing memory
+    fn release_resource(&mut self, resource_id: u32) -> DriverResult<()> {
+        self.submit_control_nodata(&resource_command(VIRTIO_GPU_CMD_RESOURCE_UNREF, resource_id))?;
+        self.resource_refs.borrow_mut().forget(resource_id);
+        self.graphics_manager.blobs.remove(&resource_id);
+        self.graphics_manager.uuids.remove(&resource_id);
+        if let Some(resource) = self.graphics_manager.resources.remove(&resource_id) {
+            if resource.memory_address != 0 {
+                self.memory_manager.free(resource.memory_address)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Record a use of a resource's memory, delaying its eviction
+    fn touch_resource(&mut self, resource_id: u32) {
+        if let Some(address) = self.graphics_manager.get_resource(resource_id).map(|resource| resource.memory_address) {
+            self.memory_manager.touch(address);
+        }
+    }
+
+    /// Mark a resource with backing memory as a cache whose content its
+    /// owner can recreate: it may then be destroyed when its pool runs out
+    pub fn set_resource_cached(&mut self, resource_id: u32, cached: bool) -> DriverResult<()> {
+        let address = self
+            .graphics_manager
+            .get_resource(resource_id)
+            .map(|resource| resource.memory_address)
+            .filter(|&address| address != 0)
+            .ok_or(DriverError::InvalidParameter)?;
+        self.memory_manager.set_evictable(address, cached)
+    }
+
+    /// Whether a resource still exists, for owners of cached resources
+    pub fn resource_exists(&self, resource_id: u32) -> bool {
+        self.graphics_manager.resources.contains_key(&resource_id)
+    }
+
+    /// Usage and fragmentation of every GPU memory pool
+    pub fn gpu_memory_stats(&self) -> Vec<MemoryPoolStats> {
+        self.memory_manager.all_pool_stats()
+    }
+}
+
+// ========================================
+// RESOURCE LIFETIME
+// ========================================
+
+impl VirtioGpuDriver {
+    fn next_resource_id(&self) -> u32 {
+        self.graphics_manager.resources.keys().next_back().map_or(1, |last| last + 1)
+    }
+
+    fn resource_handle(&self, resource_id: u32) -> ResourceHandle {
+        let serial = self.resource_refs.borrow_mut().register(resource_id);
+        ResourceHandle { id: resource_id, serial, refs: self.resource_refs.clone() }
+    }
+
+    /// Destroy the resources whose last handle was dropped since the
+    /// previous call, returning how many went
+    pub fn reap_orphaned_resources(&mut self) -> DriverResult<usize> {
+        let orphaned = core::mem::take(&mut self.resource_refs.borrow_mut().orphaned);
+        let mut reaped = 0;
+        for resource_id in orphaned {
+            if self.graphics_manager.resources.contains_key(&resource_id) {
+                self.evict_resource(resource_id)?;
+                reaped += 1;
+            }
+        }
+        Ok(reaped)
+    }
+
+    /// Make sure a resource id is free on the host: orphans are destroyed
+    /// first, then cached resources, least recently used first, while every
+    /// slot is taken. OutOfMemory if nothing can go
+    fn reserve_resource_slot(&mut self) -> DriverResult<()> {
+        self.reap_orphaned_resources()?;
+        while self.graphics_manager.resources.len() >= VIRTIO_GPU_MAX_RESOURCES {
+            let victim = self.memory_manager.any_eviction_candidate().ok_or(DriverError::OutOfMemory)?;
+            self.evict_resource(victim)?;
+        }
+        Ok(())
+    }
+
+    /// Destroy a context and the resources it owns. Resources someone still
+    /// holds a handle to are only detached from it
+    pub fn destroy_context(&mut self, ctx_id: u32) -> DriverResult<()> {
+        let resources = match self.graphics_manager.contexts.get(&ctx_id) {
+            Some(context) => context.active_resources.clone(),
+            None => return Err(DriverError::InvalidParameter),
+        };
+        for resource_id in resources {
+            if self.resource_refs.borrow().is_held(resource_id) {
+                self.detach_from_context(ctx_id, resource_id)?;
+            } else {
+                self.evict_resource(resource_id)?;
+            }
+        }
+
+        self.submit_control_nodata(&control_header(VIRTIO_GPU_CMD_CTX_DESTROY, ctx_id))?;
+        self.graphics_manager.contexts.remove(&ctx_id);
+        Ok(())
+    }
+}
+
+// ========================================
+// COMPUTE CONTEXTS
+// ========================================
+
+/// One compute launch: shader, bound storage buffers and grid size
+#[derive(Debug, Clone)]
+pub struct ComputeDispatch {
+    pub shader: u32,
+    /// Storage buffers bound to slots 0.. as (resource, offset, length)
+    pub buffers: Vec<(u32, u32, u32)>,
+    pub block: [u32; 3],
+    pub grid: [u32; 3],
+}
+
+/// Encoder for the virgl command stream submitted with SUBMIT_3D
+pub struct VirglCommandStream {
+    words: Vec<u32>,
+}
+
+impl VirglCommandStream {
+    pub fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    fn command(&mut self, command: u32, object: u32, payload: &[u32]) {
+        self.words.push(command | (object << 8) | ((payload.len() as u32) << 16));
+        self.words.extend_from_slice(payload);
+    }
+
+    /// Create a compute shader from its TGSI text
+    pub fn create_compute_shader(&mut self, handle: u32, tgsi: &str, local_memory: u32) {
+        // The text is sent NUL-terminated and padded to whole words
+        let length = tgsi.len() as u32 + 1;
+        let mut payload = vec![handle, PIPE_SHADER_COMPUTE, length & 0x7fff_ffff, 300, local_memory];
+        let mut text = tgsi.as_bytes().to_vec();
+        text.push(0);
+        for chunk in text.chunks(4) {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            payload.push(u32::from_le_bytes(word));
+        }
+        self.command(VIRGL_CCMD_CREATE_OBJECT, VIRGL_OBJECT_SHADER, &payload);
+    }
+
+    pub fn bind_compute_shader(&mut self, handle: u32) {
+        self.command(VIRGL_CCMD_BIND_SHADER, 0, &[handle, PIPE_SHADER_COMPUTE]);
+    }
+
+    pub fn set_storage_buffers(&mut self, buffers: &[(u32, u32, u32)]) {
+        let mut payload = vec![PIPE_SHADER_COMPUTE, 0];
+        for &(resource, offset, length) in buffers {
+            payload.extend_from_slice(&[offset, length, resource]);
+        }
+        self.command(VIRGL_CCMD_SET_SHADER_BUFFERS, 0, &payload);
+    }
+
+    pub fn launch_grid(&mut self, block: [u32; 3], grid: [u32; 3]) {
+        let payload = [block[0], block[1], block[2], grid[0], grid[1], grid[2], 0, 0];
+        self.command(VIRGL_CCMD_LAUNCH_GRID, 0, &payload);
+    }
+
+    pub fn memory_barrier(&mut self, flags: u32) {
+        self.command(VIRGL_CCMD_MEMORY_BARRIER, 0, &[flags]);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
+
+/// Control command header for `command` on context `ctx_id`
+fn control_header(command: u32, ctx_id: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&command.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes()); // Flags
+    header.extend_from_slice(&0u64.to_le_bytes()); // Fence ID
+    header.extend_from_slice(&ctx_id.to_le_bytes());
+    header.extend_from_slice(&[0u8; 4]); // Ring index and padding
+    header
+}
+
+/// Header and rectangle shared by the 2D transfer and flush commands
+fn rect_command(command: u32, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+    let mut cmd = control_header(command, 0);
+    for value in [x, y, width, height] {
+        cmd.extend_from_slice(&value.to_le_bytes());
+    }
+    cmd
+}
+
+/// TRANSFER_TO_HOST_3D / TRANSFER_FROM_HOST_3D of a byte range of a buffer
+fn buffer_transfer_command(command: u32, ctx_id: u32, resource_id: u32, offset: u32, length: u32) -> Vec<u8> {
+    let mut cmd = control_header(command, ctx_id);
+    for value in [offset, 0, 0, length, 1, 1] {
+        cmd.extend_from_slice(&value.to_le_bytes()); // Box x, y, z, w, h, d
+    }
+    cmd.extend_from_slice(&(offset as u64).to_le_bytes());
+    for value in [resource_id, 0, 0, 0] {
+        cmd.extend_from_slice(&value.to_le_bytes()); // Level, stride, layer stride
+    }
+    cmd
+}
+
+impl VirtioGpuDriver {
+    /// Send one control command and wait for the response type
+    fn submit_control(&mut self, command: &[u8]) -> DriverResult<u32> {
+        let response = self.submit_control_response(command, 24)?;
+        Ok(u32::from_le_bytes([response[0], response[1], response[2], response[3]]))
+    }
+
+    /// Send one control command and return its `response_size`-byte response
+    fn submit_control_response(&mut self, command: &[u8], response_size: usize) -> DriverResult<Vec<u8>> {
+        let buffer = self.command_pool.alloc(command.len() + response_size).ok_or(DriverError::OutOfMemory)?;
+        let control_queue = self.control_queue.as_ref().ok_or(DriverError::General)?;
+        buffer.write(0, command);
+
+        // Device-readable command chained to the device-writable response;
+        // both go back when leaving, timeout included
+        let chain = control_queue.alloc_chain(2).ok_or(DriverError::General)?;
+        chain.set(0, buffer.addr(), command.len() as u32, 0);
+        chain.set(1, buffer.addr() + command.len() as u64, response_size as u32, VIRTQ_DESC_F_WRITE);
+
+        unsafe {
+            control_queue.add_to_avail(&chain);
+            self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
+
+            let mut timeout = 1000000;
+            while timeout > 0 {
+                if control_queue.check_used() == Some(chain.head()) {
+                    break;
+                }
+                timeout -= 1;
+                core::hint::spin_loop();
+            }
+            if timeout == 0 {
+                self.stats.errors_encountered().inc();
+                return Err(DriverError::Timeout);
+            }
+        }
+
+        self.stats.commands_processed().inc();
+        let mut bytes = vec![0u8; response_size];
+        buffer.read(command.len(), &mut bytes);
+        Ok(bytes)
+    }
+
+    fn submit_control_nodata(&mut self, command: &[u8]) -> DriverResult<()> {
+        match self.submit_control(command)? {
+            VIRTIO_GPU_RESP_OK_NODATA => Ok(()),
+            VIRTIO_GPU_RESP_ERR_OUT_OF_MEMORY => Err(DriverError::OutOfMemory),
+            _ => Err(DriverError::General),
+        }
+    }
+
+    fn compute_context(&self, ctx_id: u32) -> DriverResult<&ContextInfo> {
+        match self.graphics_manager.contexts.get(&ctx_id) {
+            Some(context) if context.context_type == ContextType::Compute => Ok(context),
+            _ => Err(DriverError::InvalidParameter),
+        }
+    }
+
+    /// Create a compute-capable 3D context (virgl capset, no scanout)
+    pub fn create_compute_context(&mut self, ctx_id: u32, name: &str) -> DriverResult<()> {
+        if !self.supports_3d {
+            return Err(DriverError::Unsupported);
+        }
+        if ctx_id == 0 || self.graphics_manager.contexts.contains_key(&ctx_id) {
+            return Err(DriverError::InvalidParameter);
+        }
+        if self.graphics_manager.contexts.len() >= VIRTIO_GPU_MAX_CONTEXTS {
+            return Err(DriverError::OutOfMemory);
+        }
+
+        let name = &name.as_bytes()[..name.len().min(64)];
+        let mut cmd = control_header(VIRTIO_GPU_CMD_CTX_CREATE, ctx_id);
+        cmd.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        cmd.extend_from_slice(&(VIRTIO_GPU_CAPSET_VIRGL2 & VIRTIO_GPU_CONTEXT_INIT_CAPSET_ID_MASK).to_le_bytes());
+        let mut debug_name = [0u8; 64];
+        debug_name[..name.len()].copy_from_slice(name);
+        cmd.extend_from_slice(&debug_name);
+        self.submit_control_nodata(&cmd)?;
+
+        self.graphics_manager.contexts.insert(ctx_id, ContextInfo {
+            id: ctx_id,
+            context_type: ContextType::Compute,
+            capabilities: vec!["COMPUTE".to_string(), "SHADER_BUFFERS".to_string()],
+            active_resources: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Allocate a storage buffer with guest backing and attach it to a compute context
+    pub fn create_storage_buffer(&mut self, ctx_id: u32, size: u32) -> DriverResult<u32> {
+        self.compute_context(ctx_id)?;
+        if size == 0 {
+            return Err(DriverError::InvalidParameter);
+        }
+        self.reserve_resource_slot()?;
+        // Memory first: making room may destroy cached resources
+        let backing = self.allocate_gpu_memory(POOL_RESOURCE, size as usize, AllocationType::Resource)?;
+        let resource_id = self.next_resource_id();
+        if let Err(error) = self.create_storage_resource(ctx_id, resource_id, backing, size) {
+            self.memory_manager.free(backing)?;
+            return Err(error);
+        }
+        self.memory_manager.set_owner(backing, resource_id);
+
+        self.graphics_manager.create_resource(ResourceInfo {
+            id: resource_id,
+            resource_type: ResourceType::StorageBuffer,
+            width: size,
+            height: 1,
+            format: PixelFormat::R8G8B8A8,
+            memory_address: backing,
+            memory_size: size as usize,
+        })?;
+        if let Some(context) = self.graphics_manager.contexts.get_mut(&ctx_id) {
+            context.active_resources.push(resource_id);
+        }
+        Ok(resource_id)
+    }
+
+    // Host side of a storage buffer: the buffer, its backing and its
+    // attachment to the context
+    fn create_storage_resource(&mut self, ctx_id: u32, resource_id: u32, backing: u64, size: u32) -> DriverResult<()> {
+        let mut cmd = control_header(VIRTIO_GPU_CMD_RESOURCE_CREATE_3D, 0);
+        for value in [resource_id, PIPE_BUFFER, VIRGL_FORMAT_R8_UNORM, VIRGL_BIND_SHADER_BUFFER, size, 1, 1, 1, 0, 0, 0, 0] {
+            cmd.extend_from_slice(&value.to_le_bytes()); // Target, format, bind, size, levels, samples, flags
+        }
+        self.submit_control_nodata(&cmd)?;
+
+        let mut cmd = control_header(VIRTIO_GPU_CMD_RESOURCE_ATTACH_BACKING, 0);
+        cmd.extend_from_slice(&resource_id.to_le_bytes());
+        cmd.extend_from_slice(&1u32.to_le_bytes()); // One memory entry
+        cmd.extend_from_slice(&backing.to_le_bytes());
+        cmd.extend_from_slice(&size.to_le_bytes());
+        cmd.extend_from_slice(&0u32.to_le_bytes());
+        self.submit_control_nodata(&cmd)?;
+
+        let mut cmd = control_header(VIRTIO_GPU_CMD_CTX_ATTACH_RESOURCE, ctx_id);
+        cmd.extend_from_slice(&resource_id.to_le_bytes());
+        cmd.extend_from_slice(&0u32.to_le_bytes());
+        self.submit_control_nodata(&cmd)
+    }
+
+    // Backing address of a storage buffer of the context, with the range checked
+    fn storage_range(&self, ctx_id: u32, resource_id: u32, offset: u32, length: usize) -> DriverResult<u64> {
+        if !self.compute_context(ctx_id)?.active_resources.contains(&resource_id) {
+            return Err(DriverError::InvalidParameter);
+        }
+        let resource = self.graphics_manager.get_resource(resource_id).ok_or(DriverError::InvalidParameter)?;
+        if resource.resource_type != ResourceType::StorageBuffer
+            || offset as usize > resource.memory_size
+            || length > resource.memory_size - offset as usize
+        {
+            return Err(DriverError::InvalidParameter);
+        }
+        Ok(resource.memory_address + offset as u64)
+    }
+
+    /// Fill part of a storage buffer and upload it to the host
+    pub fn write_storage_buffer(&mut self, ctx_id: u32, resource_id: u32, offset: u32, data: &[u8]) -> DriverResult<()> {
+        let address = self.storage_range(ctx_id, resource_id, offset, data.len())?;
+        self.touch_resource(resource_id);
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), address as *mut u8, data.len());
+        }
+        let cmd = buffer_transfer_command(VIRTIO_GPU_CMD_TRANSFER_TO_HOST_3D, ctx_id, resource_id, offset, data.len() as u32);
+        self.submit_control_nodata(&cmd)?;
+        self.stats.bytes_transferred().add(data.len() as u64);
+        Ok(())
+    }
+
+    /// Create a compute shader in the context from TGSI text
+    pub fn create_compute_shader(&mut self, ctx_id: u32, tgsi: &str, local_memory: u32) -> DriverResult<u32> {
+        self.compute_context(ctx_id)?;
+        let handle = self.graphics_manager.shaders.keys().next_back().map_or(1, |last| last + 1);
+        let mut stream = VirglCommandStream::new();
+        stream.create_compute_shader(handle, tgsi, local_memory);
+        self.submit_3d(ctx_id, &stream.into_bytes())?;
+
+        self.graphics_manager.shaders.insert(handle, ShaderInfo {
+            id: handle,
+            shader_type: ShaderType::Compute,
+            source_code: tgsi.to_string(),
+            compiled: true,
+        });
+        Ok(handle)
+    }
+
+    /// Bind the buffers and launch the grid; results are visible to
+    /// read_storage_buffer once this returns
+    pub fn dispatch_compute(&mut self, ctx_id: u32, dispatch: &ComputeDispatch) -> DriverResult<()> {
+        self.compute_context(ctx_id)?;
+        if dispatch.buffers.len() > VIRGL_MAX_SHADER_BUFFERS || dispatch.grid.contains(&0) || dispatch.block.contains(&0) {
+            return Err(DriverError::InvalidParameter);
+        }
+        match self.graphics_manager.shaders.get(&dispatch.shader) {
+            Some(shader) if shader.shader_type == ShaderType::Compute => {}
+            _ => return Err(DriverError::InvalidParameter),
+        }
+        for &(resource_id, offset, length) in dispatch.buffers.iter() {
+            self.storage_range(ctx_id, resource_id, offset, length as usize)?;
+        }
+
+        let mut stream = VirglCommandStream::new();
+        stream.bind_compute_shader(dispatch.shader);
+        stream.set_storage_buffers(&dispatch.buffers);
+        stream.launch_grid(dispatch.block, dispatch.grid);
+        stream.memory_barrier(PIPE_BARRIER_SHADER_BUFFER | PIPE_BARRIER_MAPPED_BUFFER);
+        self.submit_3d(ctx_id, &stream.into_bytes())
+    }
+
+    /// Download part of a storage buffer from the host
+    pub fn read_storage_buffer(&mut self, ctx_id: u32, resource_id: u32, offset: u32, out: &mut [u8]) -> DriverResult<()> {
+        let address = self.storage_range(ctx_id, resource_id, offset, out.len())?;
+        self.touch_resource(resource_id);
+        let cmd = buffer_transfer_command(VIRTIO_GPU_CMD_TRANSFER_FROM_HOST_3D, ctx_id, resource_id, offset, out.len() as u32);
+        self.submit_control_nodata(&cmd)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(address as *const u8, out.as_mut_ptr(), out.len());
+        }
+        self.stats.bytes_transferred().add(out.len() as u64);
+        Ok(())
+    }
+
+    /// Destroy a compute context and the storage buffers attached to it
+    pub fn destroy_compute_context(&mut self, ctx_id: u32) -> DriverResult<()> {
+        self.compute_context(ctx_id)?;
+        self.destroy_context(ctx_id)
+    }
+}
+
+// ========================================
+// BLOB RESOURCES
+// ========================================
+
+/// Where the storage of a blob resource lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobMemory {
+    /// Guest pages only, handed to the host as backing
+    Guest,
+    /// Host-allocated memory owned by a 3D context, mapped through the host-visible region
+    Host3d,
+    /// Host-allocated memory with guest backing kept in sync by the host
+    Host3dGuest,
+}
+
+impl BlobMemory {
+    fn as_u32(self) -> u32 {
+        match self {
+            BlobMemory::Guest => VIRTIO_GPU_BLOB_MEM_GUEST,
+            BlobMemory::Host3d => VIRTIO_GPU_BLOB_MEM_HOST3D,
+            BlobMemory::Host3dGuest => VIRTIO_GPU_BLOB_MEM_HOST3D_GUEST,
+        }
+    }
+
+    fn has_guest_backing(self) -> bool {
+        self != BlobMemory::Host3d
+    }
+}
+
+/// Cache attributes the host requires for a blob mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapCaching {
+    Cached,
+    Uncached,
+    WriteCombined,
+}
+
+impl MapCaching {
+    fn from_map_info(map_info: u32) -> Option<Self> {
+        match map_info & VIRTIO_GPU_MAP_CACHE_MASK {
+            VIRTIO_GPU_MAP_CACHE_CACHED => Some(MapCaching::Cached),
+            VIRTIO_GPU_MAP_CACHE_UNCACHED => Some(MapCaching::Uncached),
+            VIRTIO_GPU_MAP_CACHE_WC => Some(MapCaching::WriteCombined),
+            _ => None,
+        }
+    }
+}
+
+/// A host blob mapped into the guest physical address space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobMapping {
+    pub address: u64,
+    pub size: u64,
+    pub caching: MapCaching,
+}
+
+/// Blob resource bookkeeping, next to its ResourceInfo entry
+#[derive(Debug, Clone)]
+pub struct BlobResource {
+    memory: BlobMemory,
+    flags: u32,
+    ctx_id: u32,
+    size: u64,
+    mapping: Option<BlobMapping>,
+}
+
+/// Host-visible shared memory region that mappable blobs are placed in
+#[derive(Debug, Clone)]
+pub struct HostVisibleRegion {
+    base: u64,
+    size: u64,
+    /// Offset -> length of the windows in use
+    windows: BTreeMap<u64, u64>,
+}
+
+impl HostVisibleRegion {
+    fn new(base: u64, size: u64) -> Self {
+        Self { base, size, windows: BTreeMap::new() }
+    }
+
+    /// First-fit page-aligned window of `size` bytes
+    fn reserve(&mut self, size: u64) -> Option<u64> {
+        let size = size.checked_add(VIRTIO_GPU_BLOB_PAGE_SIZE - 1)? & !(VIRTIO_GPU_BLOB_PAGE_SIZE - 1);
+        let mut offset = 0;
+        for (&start, &length) in self.windows.iter() {
+            if start - offset >= size {
+                break;
+            }
+            offset = start + length;
+        }
+        if size > self.size || offset > self.size - size {
+            return None;
+        }
+        self.windows.insert(offset, size);
+        Some(offset)
+    }
+
+    fn release(&mut self, offset: u64) {
+        self.windows.remove(&offset);
+    }
+}
+
+/// RESOURCE_CREATE_BLOB with the guest memory entries appended
+fn create_blob_command(
+    resource_id: u32,
+    ctx_id: u32,
+    memory: BlobMemory,
+    flags: u32,
+    blob_id: u64,
+    size: u64,
+    entries: &[(u64, u32)],
+) -> Vec<u8> {
+    let mut cmd = control_header(VIRTIO_GPU_CMD_RESOURCE_CREATE_BLOB, ctx_id);
+    for value in [resource_id, memory.as_u32(), flags, entries.len() as u32] {
+        cmd.extend_from_slice(&value.to_le_bytes());
+    }
+    cmd.extend_from_slice(&blob_id.to_le_bytes());
+    cmd.extend_from_slice(&size.to_le_bytes());
+    for &(address, length) in entries {
+        cmd.extend_from_slice(&address.to_le_bytes());
+        cmd.extend_from_slice(&length.to_le_bytes());
+        cmd.extend_from_slice(&0u32.to_le_bytes());
+    }
+    cmd
+}
+
+/// Control command that only carries a resource id (UNMAP_BLOB, ASSIGN_UUID, UNREF)
+fn resource_command(command: u32, resource_id: u32) -> Vec<u8> {
+    let mut cmd = control_header(command, 0);
+    cmd.extend_from_slice(&resource_id.to_le_bytes());
+    cmd.extend_from_slice(&0u32.to_le_bytes());
+    cmd
+}
+
+impl VirtioGpuDriver {
+    /// Negotiate the device features the driver implements and locate the
+    /// host-visible memory region used for mappable blobs
+    fn negotiate_features(&mut self) -> DriverResult<()> {
+        const SUPPORTED: u64 = VIRTIO_F_VERSION_1
+            | VIRTIO_GPU_F_VIRGL
+            | VIRTIO_GPU_F_EDID
+            | VIRTIO_GPU_F_RESOURCE_UUID
+            | VIRTIO_GPU_F_RESOURCE_BLOB;
+
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, 0)?;
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER)?;
+
+        let mut offered = 0u64;
+        for half in 0..2u32 {
+            self.mmio.write_u32(VIRTIO_MMIO_DEVICE_FEATURES_SEL, half)?;
+            offered |= (self.mmio.read_u32(VIRTIO_MMIO_DEVICE_FEATURES)? as u64) << (32 * half);
+        }
+        let features = offered & SUPPORTED;
+        for half in 0..2u32 {
+            self.mmio.write_u32(VIRTIO_MMIO_DRIVER_FEATURES_SEL, half)?;
+            self.mmio.write_u32(VIRTIO_MMIO_DRIVER_FEATURES, (features >> (32 * half)) as u32)?;
+        }
+
+        let status = VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER | VIRTIO_STATUS_FEATURES_OK;
+        self.mmio.write_u32(VIRTIO_MMIO_STATUS, status)?;
+        if self.mmio.read_u32(VIRTIO_MMIO_STATUS)? & VIRTIO_STATUS_FEATURES_OK == 0 {
+            self.mmio.write_u32(VIRTIO_MMIO_STATUS, VIRTIO_STATUS_FAILED)?;
+            return Err(DriverError::Unsupported);
+        }
+        self.features = features;
+        self.supports_3d = features & VIRTIO_GPU_F_VIRGL != 0;
+
+        // A length of all ones means the region does not exist
+        self.host_visible = None;
+        if features & VIRTIO_GPU_F_RESOURCE_BLOB != 0 {
+            self.mmio.write_u32(VIRTIO_MMIO_SHM_SEL, VIRTIO_GPU_SHM_ID_HOST_VISIBLE)?;
+            let size = (self.mmio.read_u32(VIRTIO_MMIO_SHM_LEN_HIGH)? as u64) << 32
+                | self.mmio.read_u32(VIRTIO_MMIO_SHM_LEN_LOW)? as u64;
+            let base = (self.mmio.read_u32(VIRTIO_MMIO_SHM_BASE_HIGH)? as u64) << 32
+                | self.mmio.read_u32(VIRTIO_MMIO_SHM_BASE_LOW)? as u64;
+            if size != 0 && size != u64::MAX {
+                self.host_visible = Some(HostVisibleRegion::new(base, size));
+            }
+        }
+        Ok(())
+    }
+
+    fn blob(&self, resource_id: u32) -> DriverResult<&BlobResource> {
+        self.graphics_manager.blobs.get(&resource_id).ok_or(DriverError::InvalidParameter)
+    }
+
+    /// Create a blob resource of `size` bytes
+    ///
+    /// Host blobs need a 3D context and are named by the context-specific
+    /// `blob_id`; guest blobs get freshly allocated guest backing.
+    pub fn create_blob_resource(
+        &mut self,
+        ctx_id: u32,
+        memory: BlobMemory,
+        flags: u32,
+        blob_id: u64,
+        size: u64,
+    ) -> DriverResult<u32> {
+        if self.features & VIRTIO_GPU_F_RESOURCE_BLOB == 0 {
+            return Err(DriverError::Unsupported);
+        }
+        let known = VIRTIO_GPU_BLOB_FLAG_USE_MAPPABLE
+            | VIRTIO_GPU_BLOB_FLAG_USE_SHAREABLE
+            | VIRTIO_GPU_BLOB_FLAG_USE_CROSS_DEVICE;
+        if size == 0 || size > u32::MAX as u64 || flags & !known != 0 {
+            return Err(DriverError::InvalidParameter);
+        }
+        if flags & VIRTIO_GPU_BLOB_FLAG_USE_CROSS_DEVICE != 0 && self.features & VIRTIO_GPU_F_RESOURCE_UUID == 0 {
+            return Err(DriverError::Unsupported);
+        }
+        if memory != BlobMemory::Guest && (!self.supports_3d || ctx_id == 0) {
+            return Err(DriverError::InvalidParameter);
+        }
+        self.reserve_resource_slot()?;
+
+        let backing = if memory.has_guest_backing() {
+            self.allocate_gpu_memory(POOL_RESOURCE, size as usize, AllocationType::Resource)?
+        } else {
+            0
+        };
+        let resource_id = self.next_resource_id();
+        let entries: &[(u64, u32)] = if memory.has_guest_backing() { &[(backing, size as u32)] } else { &[] };
+        let blob_id = if memory == BlobMemory::Guest { 0 } else { blob_id };
+        let created = self.submit_control_nodata(&create_blob_command(resource_id, ctx_id, memory, flags, blob_id, size, entries));
+        if let Err(error) = created {
+            if backing != 0 {
+                self.memory_manager.free(backing)?;
+            }
+            return Err(error);
+        }
+        if backing != 0 {
+            self.memory_manager.set_owner(backing, resource_id);
+        }
+
+        if ctx_id != 0 {
+            let mut cmd = control_header(VIRTIO_GPU_CMD_CTX_ATTACH_RESOURCE, ctx_id);
+            cmd.extend_from_slice(&resource_id.to_le_bytes());
+            cmd.extend_from_slice(&0u32.to_le_bytes());
+            self.submit_control_nodata(&cmd)?;
+            if let Some(context) = self.graphics_manager.contexts.get_mut(&ctx_id) {
+                context.active_resources.push(resource_id);
+            }
+        }
+
+        self.graphics_manager.create_resource(ResourceInfo {
+            id: resource_id,
+            resource_type: ResourceType::Blob,
+            width: size as u32,
+            height: 1,
+            format: PixelFormat::R8G8B8A8,
+            memory_address: backing,
+            memory_size: if memory.has_guest_backing() { size as usize } else { 0 },
+        })?;
+        self.graphics_manager.blobs.insert(resource_id, BlobResource { memory, flags, ctx_id, size, mapping: None });
+        Ok(resource_id)
+    }
+
+    /// Map a mappable host blob into the host-visible region
+    ///
+    /// The returned guest physical range must be mapped with the cache
+    /// attributes the host reported.
+    pub fn map_blob(&mut self, resource_id: u32) -> DriverResult<BlobMapping> {
+        let blob = self.blob(resource_id)?;
+        if blob.memory == BlobMemory::Guest || blob.flags & VIRTIO_GPU_BLOB_FLAG_USE_MAPPABLE == 0 {
+            return Err(DriverError::InvalidParameter);
+        }
+        if let Some(mapping) = blob.mapping {
+            return Ok(mapping);
+        }
+        let size = blob.size;
+        let region = self.host_visible.as_mut().ok_or(DriverError::Unsupported)?;
+        let offset = region.reserve(size).ok_or(DriverError::OutOfMemory)?;
+        let base = region.base;
+
+        let mut cmd = control_header(VIRTIO_GPU_CMD_RESOURCE_MAP_BLOB, 0);
+        cmd.extend_from_slice(&resource_id.to_le_bytes());
+        cmd.extend_from_slice(&0u32.to_le_bytes());
+        cmd.extend_from_slice(&offset.to_le_bytes());
+        let response = match self.submit_control_response(&cmd, 32) {
+            Ok(response) => response,
+            Err(error) => {
+                if let Some(region) = self.host_visible.as_mut() {
+                    region.release(offset);
+                }
+                return Err(error);
+            }
+        };
+
+        let word = |at: usize| u32::from_le_bytes([response[at], response[at + 1], response[at + 2], response[at + 3]]);
+        let caching = if word(0) == VIRTIO_GPU_RESP_OK_MAP_INFO { MapCaching::from_map_info(word(24)) } else { None };
+        let Some(caching) = caching else {
+            if word(0) == VIRTIO_GPU_RESP_OK_MAP_INFO {
+                // Mapped with attributes we cannot honour: undo it
+                self.submit_control_nodata(&resource_command(VIRTIO_GPU_CMD_RESOURCE_UNMAP_BLOB, resource_id))?;
+            }
+            if let Some(region) = self.host_visible.as_mut() {
+                region.release(offset);
             }
+            self.stats.errors_encountered().inc();
+            return Err(DriverError::General);
+        };
+
+        let mapping = BlobMapping { address: base + offset, size, caching };
+        if let Some(blob) = self.graphics_manager.blobs.get_mut(&resource_id) {
+            blob.mapping = Some(mapping);
         }
-        
-        Ok(())
+        Ok(mapping)
     }
-    
-    /// Handle configuration changes
-    fn handle_config_change(&mut self) -> DriverResult<()> {
-        // Handle display hotplug, resolution changes, and other config events
-        let config_generation = self.mmio.read_u32(VIRTIO_MMIO_CONFIG + 4)?;
-        
-        if config_generation != self.last_config_generation {
-            // Configuration has changed, update display information
-            self.get_display_info()?;
-            
-            // Check for new scanouts
-            let num_scanouts = self.mmio.read_u32(VIRTIO_MMIO_CONFIG + 8)?;
-            if num_scanouts != self.num_scanouts {
-                self.num_scanouts = num_scanouts;
-                
-                // Update display manager with new scanout information
-                for i in 0..self.num_scanouts {
-                    let scanout_info = self.get_scanout_info(i)?;
-                    self.display_manager.update_scanout(i, scanout_info);
-                }
-            }
-            
-            // Update last known configuration generation
-            self.last_config_generation = config_generation;
-            
-            // Notify any registered configuration change handlers
-            self.handle_display_hotplug()?;
+
+    /// Remove a blob mapping from the host-visible region
+    pub fn unmap_blob(&mut self, resource_id: u32) -> DriverResult<()> {
+        let Some(mapping) = self.blob(resource_id)?.mapping else {
+            return Ok(());
+        };
+        self.submit_control_nodata(&resource_command(VIRTIO_GPU_CMD_RESOURCE_UNMAP_BLOB, resource_id))?;
+        if let Some(region) = self.host_visible.as_mut() {
+            region.release(mapping.address - region.base);
+        }
+        if let Some(blob) = self.graphics_manager.blobs.get_mut(&resource_id) {
+            blob.mapping = None;
         }
-        
         Ok(())
     }
-    
-    /// Get driver statistics
-    pub fn get_statistics(&self) -> &VirtioGpuStats {
-        &self.stats
-    }
-    
-    /// Get current driver state
-    pub fn get_state(&self) -> DriverState {
-        self.state
-    }
-    
-    /// Set power mode
-    pub fn set_power_mode(&mut self, mode_name: &str) -> DriverResult<()> {
-        if let Some(mode) = self.power_manager.power_modes.get(mode_name) {
-            // Apply power mode settings
-            self.power_manager.set_power_state(PowerState::Active)?;
-            Ok(())
-        } else {
-            Err(DriverError::InvalidParameter)
+
+    /// UUID other virtio devices use to import the resource; assigned once
+    pub fn export_resource_uuid(&mut self, resource_id: u32) -> DriverResult<[u8; 16]> {
+        if self.features & VIRTIO_GPU_F_RESOURCE_UUID == 0 {
+            return Err(DriverError::Unsupported);
         }
+        if !self.graphics_manager.resources.contains_key(&resource_id) {
+            return Err(DriverError::InvalidParameter);
+        }
+        if let Some(uuid) = self.graphics_manager.uuids.get(&resource_id) {
+            return Ok(*uuid);
+        }
+
+        let cmd = resource_command(VIRTIO_GPU_CMD_RESOURCE_ASSIGN_UUID, resource_id);
+        let response = self.submit_control_response(&cmd, 40)?;
+        if response[0..4] != VIRTIO_GPU_RESP_OK_RESOURCE_UUID.to_le_bytes() {
+            self.stats.errors_encountered().inc();
+            return Err(DriverError::General);
+        }
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(&response[24..40]);
+        self.graphics_manager.uuids.insert(resource_id, uuid);
+        Ok(uuid)
     }
-    
-    /// Get display information
-    pub fn get_display_info(&self, display_id: u32) -> Option<&DisplayInfo> {
-        self.display_manager.get_display(display_id)
+
+    /// Resource previously exported with `uuid`
+    pub fn resource_by_uuid(&self, uuid: &[u8; 16]) -> Option<u32> {
+        self.graphics_manager.uuids.iter().find(|(_, exported)| *exported == uuid).map(|(&id, _)| id)
     }
-    
-    /// Create a new resource
-    pub fn create_resource(&mut self, resource_type: ResourceType, width: u32, height: u32, format: PixelFormat) -> DriverResult<u32> {
-        let resource_id = self.graphics_manager.resources.len() as u32 + 1;
-        
-        let resource = ResourceInfo {
-            id: resource_id,
-            resource_type,
-            width,
-            height,
-            format,
-            memory_address: 0,
-            memory_size: (width * height * 4) as usize,
-        };
-        
-        self.graphics_manager.create_resource(resource)?;
-        Ok(resource_id)
+
+    /// Unmap, detach and release a blob resource
+    pub fn destroy_blob_resource(&mut self, resource_id: u32) -> DriverResult<()> {
+        self.unmap_blob(resource_id)?;
+        let ctx_id = self.blob(resource_id)?.ctx_id;
+        if ctx_id != 0 {
+            self.detach_from_context(ctx_id, resource_id)?;
+        }
+
+        self.release_resource(resource_id)
     }
 }
 
@@ -1666,7 +2844,78 @@ mod tests {
         let allocation = manager.allocate_memory(1024, MemoryPoolType::System);
         assert!(allocation.is_ok());
     }
+
+    #[test]
+    fn test_memory_manager_free_and_fragmentation() {
+        let mut manager = MemoryManager::new();
+        let first = manager.allocate(POOL_RESOURCE, 5000, AllocationType::Resource).unwrap();
+        let second = manager.allocate(POOL_RESOURCE, 4096, AllocationType::Resource).unwrap();
+        assert_eq!(second, first + 8192);
+
+        manager.free(first).unwrap();
+        let stats = manager.pool_stats(POOL_RESOURCE).unwrap();
+        assert_eq!(stats.used, 4096);
+        assert_eq!(stats.free_ranges, 2);
+        assert_eq!(stats.free - stats.largest_free, 8192);
+
+        // The hole is reused, then everything merges back
+        assert_eq!(manager.allocate(POOL_RESOURCE, 8192, AllocationType::Resource).unwrap(), first);
+        manager.free(first).unwrap();
+        manager.free(second).unwrap();
+        let stats = manager.pool_stats(POOL_RESOURCE).unwrap();
+        assert_eq!((stats.used, stats.free_ranges, stats.fragmentation_percent), (0, 1, 0));
+        assert!(matches!(manager.free(second), Err(DriverError::InvalidParameter)));
+
+        let size = stats.size as usize;
+        assert!(manager.allocate(POOL_RESOURCE, size, AllocationType::Resource).is_ok());
+        assert!(matches!(manager.allocate(POOL_RESOURCE, 1, AllocationType::Resource), Err(DriverError::OutOfMemory)));
+    }
+
+    #[test]
+    fn test_memory_manager_eviction_candidate() {
+        let mut manager = MemoryManager::new();
+        let a = manager.allocate(POOL_RESOURCE, 4096, AllocationType::Resource).unwrap();
+        let b = manager.allocate(POOL_RESOURCE, 4096, AllocationType::Resource).unwrap();
+        let c = manager.allocate(POOL_RESOURCE, 4096, AllocationType::Resource).unwrap();
+        for (address, owner) in [(a, 1), (b, 2), (c, 3)] {
+            manager.set_owner(address, owner);
+        }
+        assert_eq!(manager.eviction_candidate(POOL_RESOURCE), None);
+
+        manager.set_evictable(a, true).unwrap();
+        manager.set_evictable(b, true).unwrap();
+        assert_eq!(manager.eviction_candidate(POOL_RESOURCE), Some(1));
+        manager.touch(a);
+        assert_eq!(manager.eviction_candidate(POOL_RESOURCE), Some(2));
+        assert_eq!(manager.eviction_candidate(POOL_FRAMEBUFFER), None);
+    }
     
+    #[test]
+    fn test_resource_handles() {
+        let refs = Rc::new(RefCell::new(ResourceRefs::default()));
+        let handle = |id: u32| {
+            let serial = refs.borrow_mut().register(id);
+            ResourceHandle { id, serial, refs: refs.clone() }
+        };
+
+        let first = handle(1);
+        let copy = first.clone();
+        drop(first);
+        assert!(refs.borrow().orphaned.is_empty());
+        drop(copy);
+        assert_eq!(refs.borrow().orphaned, vec![1]);
+        assert!(!refs.borrow().is_held(1));
+
+        // A handle outliving its resource leaves the next one with the same id alone
+        let stale = handle(2);
+        refs.borrow_mut().forget(2);
+        let reused = handle(2);
+        drop(stale);
+        assert!(refs.borrow().is_held(2));
+        drop(reused);
+        assert_eq!(refs.borrow().orphaned, vec![1, 2]);
+    }
+
     #[test]
     fn test_power_manager_modes() {
         let mut manager = PowerManager::new();
@@ -1679,6 +2928,73 @@ mod tests {
         assert!(manager.power_modes.contains_key("PowerSaving"));
     }
     
+    #[test]
+    fn test_virgl_compute_stream() {
+        let mut stream = VirglCommandStream::new();
+        stream.create_compute_shader(3, "COMP", 0);
+        stream.bind_compute_shader(3);
+        stream.set_storage_buffers(&[(7, 0, 256)]);
+        stream.launch_grid([64, 1, 1], [4, 1, 1]);
+        let bytes = stream.into_bytes();
+        let words: Vec<u32> =
+            bytes.chunks(4).map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect();
+
+        // "COMP\0" is padded to two words after five header dwords
+        assert_eq!(words[0], VIRGL_CCMD_CREATE_OBJECT | (VIRGL_OBJECT_SHADER << 8) | (7 << 16));
+        assert_eq!(&words[1..4], &[3, PIPE_SHADER_COMPUTE, 5]);
+        assert_eq!(words[6], u32::from_le_bytes(*b"COMP"));
+        assert_eq!(words[7], 0);
+        assert_eq!(&words[8..11], &[VIRGL_CCMD_BIND_SHADER | (2 << 16), 3, PIPE_SHADER_COMPUTE]);
+        assert_eq!(&words[11..16], &[VIRGL_CCMD_SET_SHADER_BUFFERS | (5 << 16), PIPE_SHADER_COMPUTE, 0, 0, 256]);
+        assert_eq!(words[16], 7);
+        assert_eq!(words[17], VIRGL_CCMD_LAUNCH_GRID | (8 << 16));
+        assert_eq!(&words[18..24], &[64, 1, 1, 4, 1, 1]);
+        assert_eq!(words.len(), 26);
+    }
+
+    #[test]
+    fn test_buffer_transfer_command() {
+        let cmd = buffer_transfer_command(VIRTIO_GPU_CMD_TRANSFER_FROM_HOST_3D, 2, 9, 16, 64);
+        assert_eq!(cmd.len(), 72);
+        assert_eq!(&cmd[0..4], &VIRTIO_GPU_CMD_TRANSFER_FROM_HOST_3D.to_le_bytes());
+        assert_eq!(&cmd[16..20], &2u32.to_le_bytes());
+        assert_eq!(&cmd[24..28], &16u32.to_le_bytes()); // Box x
+        assert_eq!(&cmd[36..40], &64u32.to_le_bytes()); // Box width
+        assert_eq!(&cmd[48..56], &16u64.to_le_bytes());
+        assert_eq!(&cmd[56..60], &9u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_create_blob_command() {
+        let cmd = create_blob_command(5, 3, BlobMemory::Host3dGuest, VIRTIO_GPU_BLOB_FLAG_USE_MAPPABLE, 42, 8192, &[(0x3000000, 8192)]);
+        assert_eq!(cmd.len(), 56 + 16);
+        assert_eq!(&cmd[0..4], &VIRTIO_GPU_CMD_RESOURCE_CREATE_BLOB.to_le_bytes());
+        assert_eq!(&cmd[16..20], &3u32.to_le_bytes());
+        assert_eq!(&cmd[24..28], &5u32.to_le_bytes());
+        assert_eq!(&cmd[28..32], &VIRTIO_GPU_BLOB_MEM_HOST3D_GUEST.to_le_bytes());
+        assert_eq!(&cmd[32..36], &VIRTIO_GPU_BLOB_FLAG_USE_MAPPABLE.to_le_bytes());
+        assert_eq!(&cmd[36..40], &1u32.to_le_bytes()); // Entries
+        assert_eq!(&cmd[40..48], &42u64.to_le_bytes());
+        assert_eq!(&cmd[48..56], &8192u64.to_le_bytes());
+        assert_eq!(&cmd[56..64], &0x3000000u64.to_le_bytes());
+        assert_eq!(&cmd[64..68], &8192u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_host_visible_windows() {
+        let mut region = HostVisibleRegion::new(0x8000_0000, 16 * 4096);
+        assert_eq!(region.reserve(100), Some(0));
+        assert_eq!(region.reserve(2 * 4096), Some(4096));
+        assert_eq!(region.reserve(4096), Some(3 * 4096));
+        region.release(4096);
+        assert_eq!(region.reserve(4096), Some(4096));
+        assert_eq!(region.reserve(4096), Some(2 * 4096));
+        assert_eq!(region.reserve(13 * 4096), None);
+        assert_eq!(region.reserve(12 * 4096), Some(4 * 4096));
+        assert_eq!(MapCaching::from_map_info(VIRTIO_GPU_MAP_CACHE_WC), Some(MapCaching::WriteCombined));
+        assert_eq!(MapCaching::from_map_info(0), None);
+    }
+
     #[test]
     fn test_driver_state_transitions() {
         let mut driver = VirtioGpuDriver {
@@ -1691,15 +3007,7 @@ mod tests {
                 bars: [0; 6],
             },
             state: DriverState::Uninitialized,
-            stats: VirtioGpuStats {
-                commands_processed: AtomicU64::new(0),
-                frames_rendered: AtomicU64::new(0),
-                bytes_transferred: AtomicU64::new(0),
-                interrupts_handled: AtomicU64::new(0),
-                errors_encountered: AtomicU64::new(0),
-                last_command_time: AtomicU64::new(0),
-                performance_metrics: AtomicU64::new(0),
-            },
+            stats: VirtioGpuStats::new(),
             display_manager: DisplayManager::new(),
             graphics_manager: GraphicsManager::new(),
             memory_manager: MemoryManager::new(),
@@ -1725,64 +3033,51 @@ mod tests {
 // ========================================
 
 #[no_mangle]
-pub extern "C" fn driver_main() -> ! {
+pub extern "C" fn driver_main() {
+    // One driver for the life of the loop, so what it measures (frame
+    // times, queue occupancy) outlives a single message
+    let mut driver = match VirtioGpuDriver::init(DeviceInfo {
+        vendor_id: 0x1AF4,
+        device_id: 0x1050,
+        device_class: 0x03,
+        device_subclass: 0x00,
+        device_protocol: 0x00,
+        bars: [0; 6],
+    }) {
+        Ok(driver) => driver,
+        Err(_) => return,
+    };
+    if driver.init_graphics(1024, 768, 32).is_err() {
+        return;
+    }
+    
     // Create message loop
-    let mut message_loop = MessageLoop::new();
+    let mut message_loop = match MessageLoop::new() {
+        Ok(message_loop) => message_loop,
+        Err(_) => return,
+    };
     
     // Main driver loop
-    loop {
-        // Process messages
-        if let Some(message) = message_loop.receive_message() {
-            // Create driver instance
-            let mut driver = VirtioGpuDriver {
-                device_info: DeviceInfo {
-                    vendor_id: 0x1AF4,
-                    device_id: 0x1050,
-                    device_class: 0x03,
-                    device_subclass: 0x00,
-                    device_protocol: 0x00,
-                    bars: [0; 6],
-                },
-                state: DriverState::Uninitialized,
-                stats: VirtioGpuStats {
-                    commands_processed: AtomicU64::new(0),
-                    frames_rendered: AtomicU64::new(0),
-                    bytes_transferred: AtomicU64::new(0),
-                    interrupts_handled: AtomicU64::new(0),
-                    errors_encountered: AtomicU64::new(0),
-                    last_command_time: AtomicU64::new(0),
-                    performance_metrics: AtomicU64::new(0),
-                },
-                display_manager: DisplayManager::new(),
-                graphics_manager: GraphicsManager::new(),
-                memory_manager: MemoryManager::new(),
-                performance_monitor: PerformanceMonitor::new(),
-                power_manager: PowerManager::new(),
-                debug_manager: DebugManager::new(),
-                control_queue: None,
-                cursor_queue: None,
-                queue_memory: None,
-                supports_3d: true,
-            };
-            
-            // Initialize driver
-            if let Ok(()) = driver.init_graphics(1024, 768, 32) {
-                // Handle the message
-                if let Err(e) = driver.handle_message(&message) {
-                    // Log error
-                    driver.debug_manager.log_error(DebugError {
-                        error_code: 1,
-                        error_message: format!("Failed to handle message: {:?}", e),
-                        timestamp: 0,
-                        severity: LogLevel::Error,
-                    });
-                }
+    let result = message_loop.run(
+        "virtio-gpu",
+        "1.0.0",
+        &[0x1AF4], // VirtIO vendor ID
+        &[0x1050], // GPU device ID
+        |ipc, message| {
+            let result = driver.handle_message(&message, ipc);
+            if let Err(e) = &result {
+                driver.debug_manager.log_error(DebugError {
+                    error_code: 1,
+                    error_message: format!("Failed to handle message: {:?}", e),
+                    timestamp: monotonic_ns(),
+                    severity: LogLevel::Error,
+                });
             }
+            result
         }
-        
-        // Yield to other tasks
-        core::hint::spin_loop();
-    }
+    );
+    
+    let _ = result;
 }
     fn init_graphics(&mut self, width: u32, height: u32, bpp: u8) -> DriverResult<()> {
         // Real graphics initialization implementation
@@ -1802,9 +3097,14 @@ pub extern "C" fn driver_main() -> ! {
         // Allocate memory for the framebuffer
         let framebuffer_size = (width * height * (bpp / 8)) as usize;
         
-        // Allocate memory for the framebuffer using kernel memory management
+        // Allocate memory for the framebuffer using kernel memory management,
+        // the one of the previous mode going back to the pool first
         let framebuffer_size = (width * height * (bpp / 8)) as usize;
-        let framebuffer_addr = self.memory_manager.allocate_framebuffer(framebuffer_size)?;
+        if self.framebuffer_info.physical_address != 0 {
+            self.memory_manager.free(self.framebuffer_info.physical_address)?;
+            self.framebuffer_info.physical_address = 0;
+        }
+        let framebuffer_addr = self.allocate_gpu_memory(POOL_FRAMEBUFFER, framebuffer_size, AllocationType::Framebuffer)?;
         
         // Create resource via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -1821,17 +3121,13 @@ pub extern "C" fn driver_main() -> ! {
                 cmd[16..20].copy_from_slice(&VIRTIO_GPU_FORMAT_B8G8R8A8_UNORM.to_le_bytes()); // Format
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = framebuffer_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, framebuffer_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -1840,7 +3136,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -1855,7 +3151,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
             }
         }
         
@@ -1881,17 +3177,13 @@ pub extern "C" fn driver_main() -> ! {
                 cmd[24..28].copy_from_slice(&height.to_le_bytes());
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = framebuffer_addr + 32; // Command after framebuffer
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, framebuffer_addr + 32, cmd.len() as u32, 0); // Command after framebuffer
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -1900,7 +3192,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -1915,7 +3207,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
             }
         }
         
@@ -1941,170 +3233,25 @@ pub extern "C" fn driver_main() -> ! {
             return Err(DriverError::MemoryError);
         }
         
-        // Real pixel setting implementation via VirtIO GPU commands
-        // This involves:
-        // 1. Creating a 1x1 resource for the pixel
-        // 2. Setting the pixel color data
-        // 3. Sending RESOURCE_ATTACH_BACKING command
-        // 4. Sending RESOURCE_FLUSH command
-        // 5. Updating the scanout
-        
-        // Allocate memory for the pixel resource
-        let pixel_resource_size = 4; // 32-bit color
+        // The pixel goes to the backing of the scanout resource, then only
+        // its rectangle is transferred and flushed: no resource of its own
+        let offset = (y * self.framebuffer_info.pitch + x * 4) as u64;
+        unsafe {
+            ((self.framebuffer_info.physical_address + offset) as *mut u32).write_volatile(color);
+        }
         
-        // Allocate memory for the pixel resource using kernel memory management
-        let pixel_resource_size = 4; // 32-bit color
-        let pixel_resource_addr = self.memory_manager.allocate_resource(pixel_resource_size)?;
+        let mut transfer = rect_command(VIRTIO_GPU_CMD_TRANSFER_TO_HOST_2D, x, y, 1, 1);
+        transfer.extend_from_slice(&offset.to_le_bytes());
+        transfer.extend_from_slice(&FRAMEBUFFER_RESOURCE_ID.to_le_bytes());
+        transfer.extend_from_slice(&0u32.to_le_bytes());
+        self.submit_control_nodata(&transfer)?;
         
-        // Create 1x1 resource via control queue
-        if let Some(ref mut control_queue) = self.control_queue {
-            unsafe {
-                // Prepare RESOURCE_CREATE_2D command for 1x1 resource
-                let mut cmd_buffer = [0u8; 32];
-                let cmd = &mut cmd_buffer;
-                
-                // Command header
-                cmd[0..4].copy_from_slice(&(VIRTIO_GPU_CMD_RESOURCE_CREATE_2D as u32).to_le_bytes());
-                cmd[4..8].copy_from_slice(&2u32.to_le_bytes()); // Resource ID 2 for pixel
-                cmd[8..12].copy_from_slice(&1u32.to_le_bytes()); // Width = 1
-                cmd[12..16].copy_from_slice(&1u32.to_le_bytes()); // Height = 1
-                cmd[16..20].copy_from_slice(&VIRTIO_GPU_FORMAT_B8G8R8A8_UNORM.to_le_bytes()); // Format
-                
-                // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
-                
-                // Set up descriptor
-                desc.addr = pixel_resource_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
-                
-                // Add to available ring
-                control_queue.add_to_avail(desc_head);
-                
-                // Notify device
-                self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
-                
-                // Wait for completion
-                let mut timeout = 1000000;
-                while timeout > 0 {
-                    if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
-                            break;
-                        }
-                    }
-                    timeout -= 1;
-                    for _ in 0..1000 {
-                        core::hint::spin_loop();
-                    }
-                }
-                
-                if timeout == 0 {
-                    return Err(DriverError::Timeout);
-                }
-                
-                // Free descriptor
-                control_queue.free_desc(desc_head, 1);
-                
-                // Now set the pixel color data
-                let pixel_data = color.to_le_bytes();
-                for i in 0..4 {
-                    let pixel_addr = pixel_resource_addr + 32 + i as u64; // After command buffer
-                    self.mmio.write_u8(pixel_addr as usize, pixel_data[i])?;
-                }
-                
-                // Send RESOURCE_ATTACH_BACKING command to attach the pixel data
-                let mut attach_cmd = [0u8; 32];
-                attach_cmd[0..4].copy_from_slice(&(VIRTIO_GPU_CMD_RESOURCE_ATTACH_BACKING as u32).to_le_bytes());
-                attach_cmd[4..8].copy_from_slice(&2u32.to_le_bytes()); // Resource ID
-                attach_cmd[8..16].copy_from_slice(&(pixel_resource_addr + 32).to_le_bytes()); // Memory address
-                attach_cmd[16..20].copy_from_slice(&4u32.to_le_bytes()); // Memory size
-                
-                // Allocate descriptor for attach command
-                let attach_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let attach_desc_ptr = &mut *control_queue.desc.offset(attach_desc as isize);
-                
-                // Set up descriptor
-                attach_desc_ptr.addr = pixel_resource_addr + 64; // After pixel data
-                attach_desc_ptr.len = attach_cmd.len() as u32;
-                attach_desc_ptr.flags = 0; // Write-only
-                attach_desc_ptr.next = 0;
-                
-                // Add to available ring
-                control_queue.add_to_avail(attach_desc);
-                
-                // Notify device
-                self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
-                
-                // Wait for completion
-                let mut attach_timeout = 1000000;
-                while attach_timeout > 0 {
-                    if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == attach_desc {
-                            break;
-                        }
-                    }
-                    attach_timeout -= 1;
-                    for _ in 0..1000 {
-                        core::hint::spin_loop();
-                    }
-                }
-                
-                if attach_timeout == 0 {
-                    return Err(DriverError::Timeout);
-                }
-                
-                // Free descriptor
-                control_queue.free_desc(attach_desc, 1);
-                
-                // Send RESOURCE_FLUSH command to make the pixel visible
-                let mut flush_cmd = [0u8; 32];
-                flush_cmd[0..4].copy_from_slice(&(VIRTIO_GPU_CMD_RESOURCE_FLUSH as u32).to_le_bytes());
-                flush_cmd[4..8].copy_from_slice(&x.to_le_bytes()); // X position
-                flush_cmd[8..12].copy_from_slice(&y.to_le_bytes()); // Y position
-                flush_cmd[12..16].copy_from_slice(&1u32.to_le_bytes()); // Width = 1
-                flush_cmd[16..20].copy_from_slice(&1u32.to_le_bytes()); // Height = 1
-                
-                // Allocate descriptor for flush command
-                let flush_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let flush_desc_ptr = &mut *control_queue.desc.offset(flush_desc as isize);
-                
-                // Set up descriptor
-                flush_desc_ptr.addr = pixel_resource_addr + 96; // After attach command
-                flush_desc_ptr.len = flush_cmd.len() as u32;
-                flush_desc_ptr.flags = 0; // Write-only
-                flush_desc_ptr.next = 0;
-                
-                // Add to available ring
-                control_queue.add_to_avail(flush_desc);
-                
-                // Notify device
-                self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
-                
-                // Wait for completion
-                let mut flush_timeout = 1000000;
-                while flush_timeout > 0 {
-                    if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == flush_desc {
-                            break;
-                        }
-                    }
-                    flush_timeout -= 1;
-                    for _ in 0..1000 {
-                        core::hint::spin_loop();
-                    }
-                }
-                
-                if flush_timeout == 0 {
-                    return Err(DriverError::Timeout);
-                }
-                
-                // Free descriptor
-                control_queue.free_desc(flush_desc, 1);
-            }
-        }
+        let mut flush = rect_command(VIRTIO_GPU_CMD_RESOURCE_FLUSH, x, y, 1, 1);
+        flush.extend_from_slice(&FRAMEBUFFER_RESOURCE_ID.to_le_bytes());
+        flush.extend_from_slice(&0u32.to_le_bytes());
+        self.submit_control_nodata(&flush)?;
         
+        self.stats.bytes_transferred().add(4);
         Ok(())
     }
     
@@ -2126,7 +3273,8 @@ pub extern "C" fn driver_main() -> ! {
         let buffer_resource_size = buffer.len();
         
         // Allocate memory for the buffer resource using kernel memory management
-        let buffer_resource_addr = self.memory_manager.allocate_resource(buffer_resource_size)?;
+        let buffer_resource_buffer = self.command_pool.alloc(buffer_resource_size).ok_or(DriverError::OutOfMemory)?;
+        let buffer_resource_addr = buffer_resource_buffer.addr();
         
         // Create 2D resource via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -2143,17 +3291,13 @@ pub extern "C" fn driver_main() -> ! {
                 cmd[16..20].copy_from_slice(&VIRTIO_GPU_FORMAT_B8G8R8A8_UNORM.to_le_bytes()); // Format
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = buffer_resource_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, buffer_resource_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2162,7 +3306,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -2177,7 +3321,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Copy buffer data to resource memory
                 let buffer_memory_addr = buffer_resource_addr + 32; // After command buffer
@@ -2194,17 +3338,13 @@ pub extern "C" fn driver_main() -> ! {
                 attach_cmd[16..20].copy_from_slice(&(buffer.len() as u32).to_le_bytes()); // Memory size
                 
                 // Allocate descriptor for attach command
-                let attach_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let attach_desc_ptr = &mut *control_queue.desc.offset(attach_desc as isize);
+                let attach_desc = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                attach_desc_ptr.addr = buffer_resource_addr + 32 + buffer.len() as u64; // After buffer data
-                attach_desc_ptr.len = attach_cmd.len() as u32;
-                attach_desc_ptr.flags = 0; // Write-only
-                attach_desc_ptr.next = 0;
+                attach_desc.set(0, buffer_resource_addr + 32 + buffer.len() as u64, attach_cmd.len() as u32, 0); // After buffer data
                 
                 // Add to available ring
-                control_queue.add_to_avail(attach_desc);
+                control_queue.add_to_avail(&attach_desc);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2213,7 +3353,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut attach_timeout = 1000000;
                 while attach_timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == attach_desc {
+                        if completed_id == attach_desc.head() {
                             break;
                         }
                     }
@@ -2228,7 +3368,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(attach_desc, 1);
+                drop(attach_desc);
                 
                 // Send SET_SCANOUT command to display the buffer
                 let mut scanout_cmd = [0u8; 32];
@@ -2241,17 +3381,13 @@ pub extern "C" fn driver_main() -> ! {
                 scanout_cmd[24..28].copy_from_slice(&self.framebuffer_info.height.to_le_bytes());
                 
                 // Allocate descriptor for scanout command
-                let scanout_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let scanout_desc_ptr = &mut *control_queue.desc.offset(scanout_desc as isize);
+                let scanout_desc = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                scanout_desc_ptr.addr = buffer_resource_addr + 32 + buffer.len() as u64 + 32; // After attach command
-                scanout_desc_ptr.len = scanout_cmd.len() as u32;
-                scanout_desc_ptr.flags = 0; // Write-only
-                scanout_desc_ptr.next = 0;
+                scanout_desc.set(0, buffer_resource_addr + 32 + buffer.len() as u64 + 32, scanout_cmd.len() as u32, 0); // After attach command
                 
                 // Add to available ring
-                control_queue.add_to_avail(scanout_desc);
+                control_queue.add_to_avail(&scanout_desc);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2260,7 +3396,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut scanout_timeout = 1000000;
                 while scanout_timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == scanout_desc {
+                        if completed_id == scanout_desc.head() {
                             break;
                         }
                     }
@@ -2275,7 +3411,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(scanout_desc, 1);
+                drop(scanout_desc);
                 
                 // Send RESOURCE_FLUSH command to make the buffer visible
                 let mut flush_cmd = [0u8; 32];
@@ -2287,17 +3423,13 @@ pub extern "C" fn driver_main() -> ! {
                 flush_cmd[20..24].copy_from_slice(&self.framebuffer_info.height.to_le_bytes());
                 
                 // Allocate descriptor for flush command
-                let flush_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let flush_desc_ptr = &mut *control_queue.desc.offset(flush_desc as isize);
+                let flush_desc = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                flush_desc_ptr.addr = buffer_resource_addr + 32 + buffer.len() as u64 + 64; // After scanout command
-                flush_desc_ptr.len = flush_cmd.len() as u32;
-                flush_desc_ptr.flags = 0; // Write-only
-                flush_desc_ptr.next = 0;
+                flush_desc.set(0, buffer_resource_addr + 32 + buffer.len() as u64 + 64, flush_cmd.len() as u32, 0); // After scanout command
                 
                 // Add to available ring
-                control_queue.add_to_avail(flush_desc);
+                control_queue.add_to_avail(&flush_desc);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2306,7 +3438,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut flush_timeout = 1000000;
                 while flush_timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == flush_desc {
+                        if completed_id == flush_desc.head() {
                             break;
                         }
                     }
@@ -2321,7 +3453,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(flush_desc, 1);
+                drop(flush_desc);
             }
         }
         
@@ -2339,7 +3471,8 @@ pub extern "C" fn driver_main() -> ! {
         let clear_resource_size = self.framebuffer_info.memory_size;
         
         // Allocate memory for the clear resource using kernel memory management
-        let clear_resource_addr = self.memory_manager.allocate_resource(clear_resource_size)?;
+        let clear_resource_buffer = self.command_pool.alloc(clear_resource_size).ok_or(DriverError::OutOfMemory)?;
+        let clear_resource_addr = clear_resource_buffer.addr();
         
         // Create 2D resource via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -2356,17 +3489,13 @@ pub extern "C" fn driver_main() -> ! {
                 cmd[16..20].copy_from_slice(&VIRTIO_GPU_FORMAT_B8G8R8A8_UNORM.to_le_bytes()); // Format
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = clear_resource_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, clear_resource_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2375,7 +3504,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -2390,7 +3519,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Fill the resource memory with the specified color
                 let clear_memory_addr = clear_resource_addr + 32; // After command buffer
@@ -2413,17 +3542,13 @@ pub extern "C" fn driver_main() -> ! {
                 attach_cmd[16..20].copy_from_slice(&(clear_resource_size as u32).to_le_bytes()); // Memory size
                 
                 // Allocate descriptor for attach command
-                let attach_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let attach_desc_ptr = &mut *control_queue.desc.offset(attach_desc as isize);
+                let attach_desc = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                attach_desc_ptr.addr = clear_resource_addr + 32 + clear_resource_size as u64; // After clear data
-                attach_desc_ptr.len = attach_cmd.len() as u32;
-                attach_desc_ptr.flags = 0; // Write-only
-                attach_desc_ptr.next = 0;
+                attach_desc.set(0, clear_resource_addr + 32 + clear_resource_size as u64, attach_cmd.len() as u32, 0); // After clear data
                 
                 // Add to available ring
-                control_queue.add_to_avail(attach_desc);
+                control_queue.add_to_avail(&attach_desc);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2432,7 +3557,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut attach_timeout = 1000000;
                 while attach_timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == attach_desc {
+                        if completed_id == attach_desc.head() {
                             break;
                         }
                     }
@@ -2447,7 +3572,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(attach_desc, 1);
+                drop(attach_desc);
                 
                 // Send SET_SCANOUT command to display the clear screen
                 let mut scanout_cmd = [0u8; 32];
@@ -2460,17 +3585,13 @@ pub extern "C" fn driver_main() -> ! {
                 scanout_cmd[24..28].copy_from_slice(&self.framebuffer_info.height.to_le_bytes());
                 
                 // Allocate descriptor for scanout command
-                let scanout_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let scanout_desc_ptr = &mut *control_queue.desc.offset(scanout_desc as isize);
+                let scanout_desc = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                scanout_desc_ptr.addr = clear_resource_addr + 32 + clear_resource_size as u64 + 32; // After attach command
-                scanout_desc_ptr.len = scanout_cmd.len() as u32;
-                scanout_desc_ptr.flags = 0; // Write-only
-                scanout_desc_ptr.next = 0;
+                scanout_desc.set(0, clear_resource_addr + 32 + clear_resource_size as u64 + 32, scanout_cmd.len() as u32, 0); // After attach command
                 
                 // Add to available ring
-                control_queue.add_to_avail(scanout_desc);
+                control_queue.add_to_avail(&scanout_desc);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2479,7 +3600,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut scanout_timeout = 1000000;
                 while scanout_timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == scanout_desc {
+                        if completed_id == scanout_desc.head() {
                             break;
                         }
                     }
@@ -2494,7 +3615,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(scanout_desc, 1);
+                drop(scanout_desc);
                 
                 // Send RESOURCE_FLUSH command to make the clear screen visible
                 let mut flush_cmd = [0u8; 32];
@@ -2506,17 +3627,13 @@ pub extern "C" fn driver_main() -> ! {
                 flush_cmd[20..24].copy_from_slice(&self.framebuffer_info.height.to_le_bytes());
                 
                 // Allocate descriptor for flush command
-                let flush_desc = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let flush_desc_ptr = &mut *control_queue.desc.offset(flush_desc as isize);
+                let flush_desc = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                flush_desc_ptr.addr = clear_resource_addr + 32 + clear_resource_size as u64 + 64; // After scanout command
-                flush_desc_ptr.len = flush_cmd.len() as u32;
-                flush_desc_ptr.flags = 0; // Write-only
-                flush_desc_ptr.next = 0;
+                flush_desc.set(0, clear_resource_addr + 32 + clear_resource_size as u64 + 64, flush_cmd.len() as u32, 0); // After scanout command
                 
                 // Add to available ring
-                control_queue.add_to_avail(flush_desc);
+                control_queue.add_to_avail(&flush_desc);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2525,7 +3642,7 @@ pub extern "C" fn driver_main() -> ! {
                 let mut flush_timeout = 1000000;
                 while flush_timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == flush_desc {
+                        if completed_id == flush_desc.head() {
                             break;
                         }
                     }
@@ -2540,7 +3657,7 @@ pub extern "C" fn driver_main() -> ! {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(flush_desc, 1);
+                drop(flush_desc);
             }
         }
         
@@ -2563,7 +3680,7 @@ impl VirtioGpuDriver {
                 // Read virtqueue completion ring
                 while let Some(completed_id) = control_queue.check_used() {
                     // Process the completed command
-                    let desc = &*control_queue.desc.offset(completed_id as isize);
+                    let desc = control_queue.desc.read(completed_id);
                     
                     // Read the command result from the descriptor
                     let result_data = core::slice::from_raw_parts(
@@ -2618,9 +3735,6 @@ impl VirtioGpuDriver {
                             }
                         }
                     }
-                    
-                    // Free the descriptor for reuse
-                    control_queue.free_desc(completed_id, 1);
                 }
             }
         }
@@ -2631,7 +3745,7 @@ impl VirtioGpuDriver {
                 // Read virtqueue completion ring
                 while let Some(completed_id) = cursor_queue.check_used() {
                     // Process the completed cursor command
-                    let desc = &*cursor_queue.desc.offset(completed_id as isize);
+                    let desc = cursor_queue.desc.read(completed_id);
                     
                     // Read the command result from the descriptor
                     let result_data = core::slice::from_raw_parts(
@@ -2658,9 +3772,6 @@ impl VirtioGpuDriver {
                             }
                         }
                     }
-                    
-                    // Free the descriptor for reuse
-                    cursor_queue.free_desc(completed_id, 1);
                 }
             }
         }
@@ -2705,7 +3816,8 @@ impl VirtioGpuDriver {
         let display_info_size = core::mem::size_of::<VirtioGpuRespDisplayInfo>();
         
         // Allocate memory for the display info using kernel memory management
-        let display_info_addr = self.memory_manager.allocate_resource(display_info_size)?;
+        let display_info_buffer = self.command_pool.alloc(display_info_size).ok_or(DriverError::OutOfMemory)?;
+        let display_info_addr = display_info_buffer.addr();
         
         // Send GET_DISPLAY_INFO command via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -2723,17 +3835,13 @@ impl VirtioGpuDriver {
                 cmd[21..24].copy_from_slice(&[0u8; 3]); // Padding
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = display_info_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, display_info_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2742,7 +3850,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -2757,7 +3865,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Now read the response data
                 let response_data = core::slice::from_raw_parts(
@@ -2861,7 +3969,8 @@ impl VirtioGpuDriver {
         let resource_cmd_size = core::mem::size_of::<VirtioGpuResourceCreate2d>();
         
         // Allocate memory for the resource command using kernel memory management
-        let resource_cmd_addr = self.memory_manager.allocate_resource(resource_cmd_size)?;
+        let resource_cmd_buffer = self.command_pool.alloc(resource_cmd_size).ok_or(DriverError::OutOfMemory)?;
+        let resource_cmd_addr = resource_cmd_buffer.addr();
         
         // Send RESOURCE_CREATE_2D command via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -2883,17 +3992,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&format.to_le_bytes()); // Format
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = resource_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, resource_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -2902,7 +4007,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -2917,7 +4022,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Read the response to check for success
                 let response_data = core::slice::from_raw_parts(
@@ -2963,7 +4068,8 @@ impl VirtioGpuDriver {
         let scanout_cmd_size = core::mem::size_of::<VirtioGpuSetScanout>();
         
         // Allocate memory for the scanout command using kernel memory management
-        let scanout_cmd_addr = self.memory_manager.allocate_resource(scanout_cmd_size)?;
+        let scanout_cmd_buffer = self.command_pool.alloc(scanout_cmd_size).ok_or(DriverError::OutOfMemory)?;
+        let scanout_cmd_addr = scanout_cmd_buffer.addr();
         
         // Send SET_SCANOUT command via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -2985,17 +4091,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&y.to_le_bytes()); // Y position
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = scanout_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, scanout_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -3004,7 +4106,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -3019,7 +4121,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Read the response to check for success
                 let response_data = core::slice::from_raw_parts(
@@ -3065,7 +4167,8 @@ impl VirtioGpuDriver {
         let flush_cmd_size = 32; // Standard command size
         
         // Allocate memory for the flush command using kernel memory management
-        let flush_cmd_addr = self.memory_manager.allocate_resource(flush_cmd_size)?;
+        let flush_cmd_buffer = self.command_pool.alloc(flush_cmd_size).ok_or(DriverError::OutOfMemory)?;
+        let flush_cmd_addr = flush_cmd_buffer.addr();
         
         // Send RESOURCE_FLUSH command via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -3087,17 +4190,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&y.to_le_bytes()); // Y position
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = flush_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, flush_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?;
@@ -3106,7 +4205,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -3121,7 +4220,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Read the response to check for success
                 let response_data = core::slice::from_raw_parts(
@@ -3138,6 +4237,7 @@ impl VirtioGpuDriver {
                     if response_type == VIRTIO_GPU_RESP_OK_NODATA {
                         // Resource flushed successfully
                         // The resource region is now visible
+                        self.performance_monitor.frame_presented(monotonic_ns());
                         return Ok(());
                     } else {
                         // Error response received
@@ -3170,7 +4270,8 @@ impl VirtioGpuDriver {
         let cursor_cmd_size = 32; // Standard command size
         
         // Allocate memory for the cursor command using kernel memory management
-        let cursor_cmd_addr = self.memory_manager.allocate_resource(cursor_cmd_size)?;
+        let cursor_cmd_buffer = self.command_pool.alloc(cursor_cmd_size).ok_or(DriverError::OutOfMemory)?;
+        let cursor_cmd_addr = cursor_cmd_buffer.addr();
         
         // Send MOVE_CURSOR command via cursor queue
         if let Some(ref mut cursor_queue) = self.cursor_queue {
@@ -3192,17 +4293,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&y.to_le_bytes()); // Y position
                 
                 // Allocate descriptor for command
-                let desc_head = cursor_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *cursor_queue.desc.offset(desc_head as isize);
+                let desc_chain = cursor_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = cursor_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, cursor_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                cursor_queue.add_to_avail(desc_head);
+                cursor_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 1)?; // Queue 1 for cursor
@@ -3211,7 +4308,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = cursor_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -3226,7 +4323,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                cursor_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Read the response to check for success
                 let response_data = core::slice::from_raw_parts(
@@ -3271,7 +4368,8 @@ impl VirtioGpuDriver {
         let cursor_update_cmd_size = 32; // Standard command size
         
         // Allocate memory for the cursor update command using kernel memory management
-        let cursor_update_cmd_addr = self.memory_manager.allocate_resource(cursor_update_cmd_size)?;
+        let cursor_update_cmd_buffer = self.command_pool.alloc(cursor_update_cmd_size).ok_or(DriverError::OutOfMemory)?;
+        let cursor_update_cmd_addr = cursor_update_cmd_buffer.addr();
         
         // Send UPDATE_CURSOR command via cursor queue
         if let Some(ref mut cursor_queue) = self.cursor_queue {
@@ -3293,17 +4391,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&hot_x.to_le_bytes()); // Hot spot X
                 
                 // Allocate descriptor for command
-                let desc_head = cursor_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *cursor_queue.desc.offset(desc_head as isize);
+                let desc_chain = cursor_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = cursor_update_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, cursor_update_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                cursor_queue.add_to_avail(desc_head);
+                cursor_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 1)?; // Queue 1 for cursor
@@ -3312,7 +4406,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = cursor_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -3327,7 +4421,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                cursor_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Read the response to check for success
                 let response_data = core::slice::from_raw_parts(
@@ -3377,7 +4471,8 @@ impl VirtioGpuDriver {
         let ctx_cmd_size = 32; // Standard command size
         
         // Allocate memory for the 3D context command using kernel memory management
-        let ctx_cmd_addr = self.memory_manager.allocate_resource(ctx_cmd_size)?;
+        let ctx_cmd_buffer = self.command_pool.alloc(ctx_cmd_size).ok_or(DriverError::OutOfMemory)?;
+        let ctx_cmd_addr = ctx_cmd_buffer.addr();
         
         // Send CTX_CREATE command via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -3399,17 +4494,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&0u32.to_le_bytes()); // Context flags
                 
                 // Allocate descriptor for command
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = ctx_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, ctx_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Notify device
                 self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?; // Queue 0 for control
@@ -3418,7 +4509,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -3433,7 +4524,7 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Read the response to check for success
                 let response_data = core::slice::from_raw_parts(
@@ -3484,7 +4575,8 @@ impl VirtioGpuDriver {
         let total_size = submit_cmd_size + commands.len();
         
         // Allocate memory for the 3D submission using kernel memory management
-        let submit_cmd_addr = self.memory_manager.allocate_resource(total_size)?;
+        let submit_cmd_buffer = self.command_pool.alloc(total_size).ok_or(DriverError::OutOfMemory)?;
+        let submit_cmd_addr = submit_cmd_buffer.addr();
         
         // Send SUBMIT_3D command via control queue
         if let Some(ref mut control_queue) = self.control_queue {
@@ -3506,17 +4598,13 @@ impl VirtioGpuDriver {
                 cmd[28..32].copy_from_slice(&0u32.to_le_bytes()); // Command buffer offset
                 
                 // Allocate descriptor for command header
-                let desc_head = control_queue.alloc_desc(1).ok_or(DriverError::General)?;
-                let desc = &mut *control_queue.desc.offset(desc_head as isize);
+                let desc_chain = control_queue.alloc_chain(1).ok_or(DriverError::General)?;
                 
                 // Set up descriptor
-                desc.addr = submit_cmd_addr;
-                desc.len = cmd.len() as u32;
-                desc.flags = 0; // Write-only
-                desc.next = 0;
+                desc_chain.set(0, submit_cmd_addr, cmd.len() as u32, 0); // Write-only
                 
                 // Add to available ring
-                control_queue.add_to_avail(desc_head);
+                control_queue.add_to_avail(&desc_chain);
                 
                 // Copy command buffer to memory after the command header
                 let cmd_buffer_addr = submit_cmd_addr + submit_cmd_size as u64;
@@ -3535,7 +4623,7 @@ impl VirtioGpuDriver {
                 let mut timeout = 1000000;
                 while timeout > 0 {
                     if let Some(completed_id) = control_queue.check_used() {
-                        if completed_id == desc_head {
+                        if completed_id == desc_chain.head() {
                             break;
                         }
                     }
@@ -3550,11 +4638,11 @@ impl VirtioGpuDriver {
                 }
                 
                 // Free descriptor
-                control_queue.free_desc(desc_head, 1);
+                drop(desc_chain);
                 
                 // Update statistics
-                self.stats.commands_processed.fetch_add(1, Ordering::Relaxed);
-                self.stats.bytes_transferred.fetch_add(commands.len() as u64, Ordering::Relaxed);
+                self.stats.commands_processed().inc();
+                self.stats.bytes_transferred().add(commands.len() as u64);
                 
                         return Ok(());
             }
@@ -3705,6 +4793,13 @@ impl VirtioGpuDriver {
             caps.push("3D_ACCELERATION".to_string());
         }
         
+        if self.features & VIRTIO_GPU_F_RESOURCE_BLOB != 0 {
+            caps.push("RESOURCE_BLOB".to_string());
+        }
+        if self.features & VIRTIO_GPU_F_RESOURCE_UUID != 0 {
+            caps.push("RESOURCE_UUID".to_string());
+        }
+        
         caps.push("2D_ACCELERATION".to_string());
         caps.push("MULTI_DISPLAY".to_string());
         caps.push("HARDWARE_CURSOR".to_string());
@@ -3718,11 +4813,8 @@ impl VirtioGpuDriver {
     
     /// Get performance metrics
     pub fn get_performance_metrics(&self) -> (u64, u64, u64) {
-        let commands = self.stats.commands_processed.load(Ordering::Relaxed);
-        let frames = self.stats.frames_rendered.load(Ordering::Relaxed);
-        let bytes = self.stats.bytes_transferred.load(Ordering::Relaxed);
-        
-        (commands, frames, bytes)
+        let stats = self.stats.snapshot();
+        (stats.commands_processed, stats.frames_rendered, stats.bytes_transferred)
     }
     
     /// Set debug level
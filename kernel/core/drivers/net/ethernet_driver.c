@@ -17,6 +17,8 @@
 #include <orion/string.h>
 #include <orion/spinlock.h>
 #include <orion/time.h>
+#include <orion/percpu.h>
+#include <orion/smp.h>
 #include <string.h>
 
 // Global Ethernet driver state
@@ -32,10 +34,26 @@ static orion_ethernet_config_t *ethernet_interfaces[64];
 static int interface_count = 0;
 static spinlock_t interface_lock = SPINLOCK_INITIALIZER;
 
-// Ethernet statistics
-static orion_ethernet_stats_t global_stats = {0};
+// Ethernet statistics: totals over every interface are kept per CPU so
+// the datapath never shares a cache line or a lock for them
+static DEFINE_PER_CPU(orion_ethernet_stats_t, global_stats);
 static spinlock_t stats_lock = SPINLOCK_INITIALIZER;
 
+// Counters add up across CPUs, timestamps take the latest
+static void global_stats_read(orion_ethernet_stats_t *stats)
+{
+    percpu_sum_struct_u64(&global_stats, stats, sizeof(*stats));
+
+    stats->last_rx_time = stats->last_tx_time = stats->last_change_time = 0;
+    for (uint32_t cpu = 0; cpu < MAX_CPUS; cpu++) {
+        if (!smp_cpu_is_online(cpu)) continue;
+        const orion_ethernet_stats_t *copy = per_cpu_ptr(global_stats, cpu);
+        stats->last_rx_time = MAX(stats->last_rx_time, copy->last_rx_time);
+        stats->last_tx_time = MAX(stats->last_tx_time, copy->last_tx_time);
+        stats->last_change_time = MAX(stats->last_change_time, copy->last_change_time);
+    }
+}
+
 /* ============================================================================
  * Ethernet Driver Management
  * ============================================================================ */
@@ -204,9 +222,7 @@ int orion_ethernet_get_stats(const char *name, orion_ethernet_stats_t *stats)
     }
 
     // Return global statistics if interface not found
-    spinlock_acquire(&stats_lock);
-    memcpy(stats, &global_stats, sizeof(orion_ethernet_stats_t));
-    spinlock_release(&stats_lock);
+    global_stats_read(stats);
 
     return 0;
 }
@@ -226,9 +242,7 @@ int orion_ethernet_reset_stats(const char *name)
     }
 
     // Reset global statistics
-    spinlock_acquire(&stats_lock);
-    memset(&global_stats, 0, sizeof(orion_ethernet_stats_t));
-    spinlock_release(&stats_lock);
+    percpu_clear(&global_stats, sizeof(global_stats));
 
     klog_info(KLOG_CAT_KERNEL, "Global Ethernet statistics reset");
     return 0;
@@ -476,16 +490,16 @@ void orion_ethernet_update_rx_stats(orion_ethernet_stats_t *stats, size_t frame_
         stats->rx_errors++;
     }
 
+    spinlock_release(&stats_lock);
+
     // Update global statistics
-    global_stats.rx_frames++;
-    global_stats.rx_bytes += frame_len;
-    global_stats.last_rx_time = orion_get_timestamp();
+    this_cpu_inc(global_stats.rx_frames);
+    this_cpu_add(global_stats.rx_bytes, frame_len);
+    this_cpu_write(global_stats.last_rx_time, orion_get_timestamp());
 
     if (has_errors) {
-        global_stats.rx_errors++;
+        this_cpu_inc(global_stats.rx_errors);
     }
-
-    spinlock_release(&stats_lock);
 }
 
 void orion_ethernet_update_tx_stats(orion_ethernet_stats_t *stats, size_t frame_len, bool has_errors)
@@ -502,16 +516,16 @@ void orion_ethernet_update_tx_stats(orion_ethernet_stats_t *stats, size_t frame_
         stats->tx_errors++;
     }
 
+    spinlock_release(&stats_lock);
+
     // Update global statistics
-    global_stats.tx_frames++;
-    global_stats.tx_bytes += frame_len;
-    global_stats.last_tx_time = orion_get_timestamp();
+    this_cpu_inc(global_stats.tx_frames);
+    this_cpu_add(global_stats.tx_bytes, frame_len);
+    this_cpu_write(global_stats.last_tx_time, orion_get_timestamp());
 
     if (has_errors) {
-        global_stats.tx_errors++;
+        this_cpu_inc(global_stats.tx_errors);
     }
-
-    spinlock_release(&stats_lock);
 }
 
 void orion_ethernet_update_error_stats(orion_ethernet_stats_t *stats, uint32_t error_type)
@@ -523,31 +537,31 @@ void orion_ethernet_update_error_stats(orion_ethernet_stats_t *stats, uint32_t e
     switch (error_type) {
         case ORION_ETH_ERROR_CRC:
             stats->rx_crc_errors++;
-            global_stats.rx_crc_errors++;
+            this_cpu_inc(global_stats.rx_crc_errors);
             break;
         case ORION_ETH_ERROR_FRAME:
             stats->rx_frame_errors++;
-            global_stats.rx_frame_errors++;
+            this_cpu_inc(global_stats.rx_frame_errors);
             break;
         case ORION_ETH_ERROR_FIFO:
             stats->rx_fifo_errors++;
-            global_stats.rx_fifo_errors++;
+            this_cpu_inc(global_stats.rx_fifo_errors);
             break;
         case ORION_ETH_ERROR_MISSED:
             stats->rx_missed_errors++;
-            global_stats.rx_missed_errors++;
+            this_cpu_inc(global_stats.rx_missed_errors);
             break;
         case ORION_ETH_ERROR_CARRIER:
             stats->tx_carrier_errors++;
-            global_stats.tx_carrier_errors++;
+            this_cpu_inc(global_stats.tx_carrier_errors);
             break;
         case ORION_ETH_ERROR_HEARTBEAT:
             stats->tx_heartbeat_errors++;
-            global_stats.tx_heartbeat_errors++;
+            this_cpu_inc(global_stats.tx_heartbeat_errors);
             break;
         case ORION_ETH_ERROR_WINDOW:
             stats->tx_window_errors++;
-            global_stats.tx_window_errors++;
+            this_cpu_inc(global_stats.tx_window_errors);
             break;
         default:
             break;
@@ -684,7 +698,7 @@ int orion_ethernet_init(void)
     interface_count = 0;
 
     // Initialize global statistics
-    memset(&global_stats, 0, sizeof(global_stats));
+    percpu_clear(&global_stats, sizeof(global_stats));
 
     klog_info(KLOG_CAT_KERNEL, "Ethernet subsystem initialized successfully");
     return 0;
@@ -31,7 +31,8 @@ use orion_driver::{
     MessageLoop, ReceivedMessage, IpcInterface,
 };
 use alloc::{vec::Vec, collections::BTreeMap, boxed::Box, string::String};
-use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::ring_config::{Offloads, Reconfigurable, RingConfig, RingLimits};
 
 // ========================================
 // ADVANCED E1000 CONSTANTS AND ENUMS
@@ -74,6 +75,7 @@ const E1000_TDT: usize = 0x03818;       // Transmit Descriptor Tail
 
 const E1000_RAL: usize = 0x05400;       // Receive Address Low
 const E1000_RAH: usize = 0x05404;       // Receive Address High
+const E1000_RXCSUM: usize = 0x05000;    // Receive Checksum Control
 
 // Control register bits
 const E1000_CTRL_FD: u32 = 0x00000001;     // Full duplex
@@ -88,6 +90,7 @@ const E1000_CTRL_SPD_1000: u32 = 0x00000200; // Force 1000Mb
 const E1000_CTRL_FRCSPD: u32 = 0x00000800;   // Force Speed
 const E1000_CTRL_FRCDPX: u32 = 0x00001000;   // Force Duplex
 const E1000_CTRL_RST: u32 = 0x04000000;      // Global reset
+const E1000_CTRL_VME: u32 = 0x40000000;      // VLAN mode enable (tag stripping)
 
 // Status register bits
 const E1000_STATUS_FD: u32 = 0x00000001;     // Full duplex
@@ -167,6 +170,19 @@ const E1000_CTRL_EXT_ASDCHK: u32 = 0x00000400;
 const E1000_CTRL_EXT_EIAME: u32 = 0x01000000;
 const E1000_CTRL_EXT_DRV_LOAD: u32 = 0x10000000;
 
+// Receive checksum offload
+const E1000_RXCSUM_IPOFL: u32 = 0x00000100;  // IP checksum offload
+const E1000_RXCSUM_TUOFL: u32 = 0x00000200;  // TCP/UDP checksum offload
+
+// Descriptor ring sizes: RDLEN and TDLEN are multiples of 128 bytes, so
+// rings come in eights of 16-byte descriptors; the 82542 has 256 at most
+const E1000_MIN_RING: u16 = 48;
+const E1000_MAX_RING: u16 = 4096;
+const E1000_MAX_RING_82542: u16 = 256;
+const E1000_RING_ALIGN: u16 = 8;
+// Transmit descriptor checks while waiting for the ring to drain
+const E1000_QUIESCE_POLLS: u32 = 1000;
+
 // Flow control
 const E1000_FCTTV: u32 = 0x0000FFFF;
 const E1000_FCRTL: u32 = 0x0000FFFF;
@@ -331,74 +347,52 @@ impl E1000TxDesc {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct NetworkStats {
-    pub rx_packets: AtomicU64,
-    pub tx_packets: AtomicU64,
-    pub rx_bytes: AtomicU64,
-    pub tx_bytes: AtomicU64,
-    pub rx_errors: AtomicU64,
-    pub tx_errors: AtomicU64,
-    pub rx_dropped: AtomicU64,
-    pub tx_dropped: AtomicU64,
-    pub rx_fifo_errors: AtomicU64,
-    pub tx_fifo_errors: AtomicU64,
-    pub rx_frame_errors: AtomicU64,
-    pub tx_carrier_errors: AtomicU64,
-    pub rx_crc_errors: AtomicU64,
-    pub rx_length_errors: AtomicU64,
-    pub rx_missed_errors: AtomicU64,
-    pub tx_aborted_errors: AtomicU64,
-    pub tx_window_errors: AtomicU64,
-}
-
-impl Default for NetworkStats {
-    fn default() -> Self {
-        Self {
-            rx_packets: AtomicU64::new(0),
-            tx_packets: AtomicU64::new(0),
-            rx_bytes: AtomicU64::new(0),
-            tx_bytes: AtomicU64::new(0),
-            rx_errors: AtomicU64::new(0),
-            tx_errors: AtomicU64::new(0),
-            rx_dropped: AtomicU64::new(0),
-            tx_dropped: AtomicU64::new(0),
-            rx_fifo_errors: AtomicU64::new(0),
-            tx_fifo_errors: AtomicU64::new(0),
-            rx_frame_errors: AtomicU64::new(0),
-            tx_carrier_errors: AtomicU64::new(0),
-            rx_crc_errors: AtomicU64::new(0),
-            rx_length_errors: AtomicU64::new(0),
-            rx_missed_errors: AtomicU64::new(0),
-            tx_aborted_errors: AtomicU64::new(0),
-            tx_window_errors: AtomicU64::new(0),
-        }
+orion_stats::counter_set! {
+    /// Interface counters, kept per CPU
+    pub struct NetworkStats => NetworkStatsValues {
+        rx_packets,
+        tx_packets,
+        rx_bytes,
+        tx_bytes,
+        rx_errors,
+        tx_errors,
+        rx_dropped,
+        tx_dropped,
+        rx_fifo_errors,
+        tx_fifo_errors,
+        rx_frame_errors,
+        tx_carrier_errors,
+        rx_crc_errors,
+        rx_length_errors,
+        rx_missed_errors,
+        tx_aborted_errors,
+        tx_window_errors,
     }
 }
 
 impl NetworkStats {
     pub fn increment_rx_packets(&self) {
-        self.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.rx_packets().inc();
     }
     
     pub fn increment_tx_packets(&self) {
-        self.tx_packets.fetch_add(1, Ordering::Relaxed);
+        self.tx_packets().inc();
     }
     
     pub fn add_rx_bytes(&self, bytes: u64) {
-        self.rx_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.rx_bytes().add(bytes);
     }
     
     pub fn add_tx_bytes(&self, bytes: u64) {
-        self.tx_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.tx_bytes().add(bytes);
     }
     
     pub fn increment_rx_errors(&self) {
-        self.rx_errors.fetch_add(1, Ordering::Relaxed);
+        self.rx_errors().inc();
     }
     
     pub fn increment_tx_errors(&self) {
-        self.tx_errors.fetch_add(1, Ordering::Relaxed);
+        self.tx_errors().inc();
     }
 }
 
@@ -465,27 +459,20 @@ impl PacketBuffer {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct QueueStats {
-    pub queue_id: u8,
-    pub packets_processed: AtomicU64,
-    pub bytes_processed: AtomicU64,
-    pub errors: AtomicU64,
-    pub overruns: AtomicU64,
-    pub underruns: AtomicU64,
+orion_stats::counter_set! {
+    pub struct QueueCounters => QueueCounterValues {
+        packets_processed,
+        bytes_processed,
+        errors,
+        overruns,
+        underruns,
+    }
 }
 
-impl Default for QueueStats {
-    fn default() -> Self {
-        Self {
-            queue_id: 0,
-            packets_processed: AtomicU64::new(0),
-            bytes_processed: AtomicU64::new(0),
-            errors: AtomicU64::new(0),
-            overruns: AtomicU64::new(0),
-            underruns: AtomicU64::new(0),
-        }
-    }
+#[derive(Debug, Default)]
+pub struct QueueStats {
+    pub queue_id: u8,
+    pub counters: QueueCounters,
 }
 
 #[derive(Debug, Clone)]
@@ -704,6 +691,9 @@ pub struct AdvancedE1000Driver {
     checksum_offload: bool,
     flow_control_enabled: bool,
     vlan_filtering: bool,
+    // What the device supports, and the offloads in use
+    ring_limits: RingLimits,
+    offloads: Offloads,
     
     // Statistics and Monitoring
     stats: NetworkStats,
@@ -752,6 +742,8 @@ impl AdvancedE1000Driver {
             checksum_offload: false,
             flow_control_enabled: false,
             vlan_filtering: false,
+            ring_limits: RingLimits::fixed(RingConfig { rx: 256, tx: 256 }, Offloads::NONE),
+            offloads: Offloads::NONE,
             stats: NetworkStats::default(),
             queue_stats: BTreeMap::new(),
             link_up: false,
@@ -798,6 +790,28 @@ impl AdvancedE1000Driver {
             }
         }
         
+        // Ring and offload reconfiguration is checked against these
+        let mut offloads = Offloads::NONE;
+        if self.checksum_offload {
+            offloads = offloads | Offloads::RX_CHECKSUM | Offloads::TX_CHECKSUM;
+        }
+        if self.tso_enabled {
+            offloads = offloads | Offloads::TSO;
+        }
+        if self.vlan_filtering {
+            offloads = offloads | Offloads::VLAN_STRIP | Offloads::VLAN_FILTER;
+        }
+        let max_ring = if self.device.device_id == 0x1000 { E1000_MAX_RING_82542 } else { E1000_MAX_RING };
+        self.ring_limits = RingLimits {
+            min_rx: E1000_MIN_RING,
+            max_rx: max_ring,
+            min_tx: E1000_MIN_RING,
+            max_tx: max_ring,
+            granularity: E1000_RING_ALIGN,
+            offloads,
+        };
+        self.offloads = offloads;
+        
         Ok(())
     }
 
@@ -1116,6 +1130,7 @@ impl OrionDriver for AdvancedE1000Driver {
         
         // Configure hardware
         self.configure_hardware()?;
+        self.program_offloads();
         
         // Set up interrupt handling
         self.setup_interrupts()?;
@@ -1282,6 +1297,66 @@ impl NetworkDriver for AdvancedE1000Driver {
     }
 }
 
+impl Reconfigurable for AdvancedE1000Driver {
+    fn ring_limits(&self) -> RingLimits {
+        self.ring_limits
+    }
+    
+    fn ring_config(&self) -> (RingConfig, Offloads) {
+        (RingConfig { rx: self.rx_desc_count, tx: self.tx_desc_count }, self.offloads)
+    }
+    
+    fn quiesce(&mut self) -> DriverResult<()> {
+        // Let the device send the frames it was handed
+        let mut polls = 0;
+        while self.tx_head != self.tx_tail {
+            if polls == E1000_QUIESCE_POLLS {
+                return Err(DriverError::Timeout);
+            }
+            self.process_transmit_descriptors()?;
+            polls += 1;
+        }
+        
+        // Link status changes still interrupt
+        self.hardware.clear_interrupt_mask(E1000_ICR_TXDW | E1000_ICR_TXQE | E1000_ICR_RXT0 |
+                                           E1000_ICR_RXDMT0 | E1000_ICR_RXO);
+        let rctl = self.hardware.read_register(E1000_RCTL);
+        self.hardware.write_register(E1000_RCTL, rctl & !E1000_RCTL_EN);
+        let tctl = self.hardware.read_register(E1000_TCTL);
+        self.hardware.write_register(E1000_TCTL, tctl & !E1000_TCTL_EN);
+        
+        Ok(())
+    }
+    
+    fn reprogram(&mut self, rings: RingConfig, offloads: Offloads) -> DriverResult<()> {
+        self.rx_desc_count = rings.rx;
+        self.tx_desc_count = rings.tx;
+        self.rx_head = 0;
+        self.rx_tail = 0;
+        self.tx_head = 0;
+        self.tx_tail = 0;
+        self.initialize_descriptor_rings()?;
+        
+        self.offloads = offloads;
+        self.program_offloads();
+        
+        Ok(())
+    }
+    
+    fn resume(&mut self) -> DriverResult<()> {
+        // Only the enable bits were cleared: filters, promiscuous mode and
+        // flow control are as they were
+        let rctl = self.hardware.read_register(E1000_RCTL);
+        self.hardware.write_register(E1000_RCTL, rctl | E1000_RCTL_EN);
+        let tctl = self.hardware.read_register(E1000_TCTL);
+        self.hardware.write_register(E1000_TCTL, tctl | E1000_TCTL_EN);
+        self.setup_interrupts()?;
+        
+        // Frames queued meanwhile go out on the new ring
+        self.transmit_queued_packets()
+    }
+}
+
 // ========================================
 // ADDITIONAL IMPLEMENTATIONS
 // ========================================
@@ -1309,7 +1384,7 @@ impl AdvancedE1000Driver {
         let rx_ring_addr = 0x1000000; // Virtual address for RX ring
         self.hardware.write_register(E1000_RDBAL, (rx_ring_addr & 0xFFFFFFFF) as u32);
         self.hardware.write_register(E1000_RDBAH, (rx_ring_addr >> 32) as u32);
-        self.hardware.write_register(E1000_RDLEN, (self.rx_desc_count * 16) as u32);
+        self.hardware.write_register(E1000_RDLEN, self.rx_desc_count as u32 * 16);
         self.hardware.write_register(E1000_RDH, 0);
         self.hardware.write_register(E1000_RDT, (self.rx_desc_count - 1) as u32);
         
@@ -1317,7 +1392,7 @@ impl AdvancedE1000Driver {
         let tx_ring_addr = 0x2000000; // Virtual address for TX ring
         self.hardware.write_register(E1000_TDBAL, (tx_ring_addr & 0xFFFFFFFF) as u32);
         self.hardware.write_register(E1000_TDBAH, (tx_ring_addr >> 32) as u32);
-        self.hardware.write_register(E1000_TDLEN, (self.tx_desc_count * 16) as u32);
+        self.hardware.write_register(E1000_TDLEN, self.tx_desc_count as u32 * 16);
         self.hardware.write_register(E1000_TDH, 0);
         self.hardware.write_register(E1000_TDT, 0);
         
@@ -1348,6 +1423,28 @@ impl AdvancedE1000Driver {
         Ok(())
     }
     
+    /// Program the receive offloads; transmit ones are asked for frame by
+    /// frame in the descriptors
+    fn program_offloads(&mut self) {
+        let mut rxcsum = self.hardware.read_register(E1000_RXCSUM) & !(E1000_RXCSUM_IPOFL | E1000_RXCSUM_TUOFL);
+        if self.offloads.contains(Offloads::RX_CHECKSUM) {
+            rxcsum |= E1000_RXCSUM_IPOFL | E1000_RXCSUM_TUOFL;
+        }
+        self.hardware.write_register(E1000_RXCSUM, rxcsum);
+        
+        let mut ctrl = self.hardware.read_register(E1000_CTRL) & !E1000_CTRL_VME;
+        if self.offloads.contains(Offloads::VLAN_STRIP) {
+            ctrl |= E1000_CTRL_VME;
+        }
+        self.hardware.write_register(E1000_CTRL, ctrl);
+        
+        let mut rctl = self.hardware.read_register(E1000_RCTL) & !E1000_RCTL_VFE;
+        if self.offloads.contains(Offloads::VLAN_FILTER) {
+            rctl |= E1000_RCTL_VFE;
+        }
+        self.hardware.write_register(E1000_RCTL, rctl);
+    }
+    
     fn setup_interrupts(&mut self) -> DriverResult<()> {
         let interrupt_mask = E1000_ICR_TXDW | E1000_ICR_TXQE | E1000_ICR_LSC |
                            E1000_ICR_RXT0 | E1000_ICR_RXDMT0 | E1000_ICR_RXO;
@@ -1644,14 +1741,15 @@ impl AdvancedE1000Driver {
     
     /// Get detailed statistics
     pub fn get_detailed_statistics(&self) -> String {
+        let stats = self.stats.snapshot();
         format!(
             "RX: {} packets, {} bytes, {} errors\nTX: {} packets, {} bytes, {} errors\nLink: {} at {} Mbps ({:?})",
-            self.stats.rx_packets.load(Ordering::Relaxed),
-            self.stats.rx_bytes.load(Ordering::Relaxed),
-            self.stats.rx_errors.load(Ordering::Relaxed),
-            self.stats.tx_packets.load(Ordering::Relaxed),
-            self.stats.tx_bytes.load(Ordering::Relaxed),
-            self.stats.tx_errors.load(Ordering::Relaxed),
+            stats.rx_packets,
+            stats.rx_bytes,
+            stats.rx_errors,
+            stats.tx_packets,
+            stats.tx_bytes,
+            stats.tx_errors,
             if self.link_up { "UP" } else { "DOWN" },
             self.link_speed.to_mbps(),
             self.duplex_mode
@@ -1723,12 +1821,13 @@ impl AdvancedE1000Driver {
         let mut metrics = Vec::new();
         
         for (queue_id, stats) in &self.queue_stats {
+            let counters = stats.counters.snapshot();
             metrics.push(format!(
                 "Queue {}: {} packets, {} bytes, {} errors",
                 queue_id,
-                stats.packets_processed.load(Ordering::Relaxed),
-                stats.bytes_processed.load(Ordering::Relaxed),
-                stats.errors.load(Ordering::Relaxed)
+                counters.packets_processed,
+                counters.bytes_processed,
+                counters.errors
             ));
         }
         
@@ -1741,24 +1840,7 @@ impl AdvancedE1000Driver {
     
     /// Reset statistics counters
     pub fn reset_statistics(&mut self) -> DriverResult<()> {
-        // Reset all atomic counters
-        self.stats.rx_packets.store(0, Ordering::Relaxed);
-        self.stats.tx_packets.store(0, Ordering::Relaxed);
-        self.stats.rx_bytes.store(0, Ordering::Relaxed);
-        self.stats.tx_bytes.store(0, Ordering::Relaxed);
-        self.stats.rx_errors.store(0, Ordering::Relaxed);
-        self.stats.tx_errors.store(0, Ordering::Relaxed);
-        self.stats.rx_dropped.store(0, Ordering::Relaxed);
-        self.stats.tx_dropped.store(0, Ordering::Relaxed);
-        self.stats.rx_fifo_errors.store(0, Ordering::Relaxed);
-        self.stats.tx_fifo_errors.store(0, Ordering::Relaxed);
-        self.stats.rx_frame_errors.store(0, Ordering::Relaxed);
-        self.stats.tx_carrier_errors.store(0, Ordering::Relaxed);
-        self.stats.rx_crc_errors.store(0, Ordering::Relaxed);
-        self.stats.rx_length_errors.store(0, Ordering::Relaxed);
-        self.stats.rx_missed_errors.store(0, Ordering::Relaxed);
-        self.stats.tx_aborted_errors.store(0, Ordering::Relaxed);
-        self.stats.tx_window_errors.store(0, Ordering::Relaxed);
+        self.stats.reset();
         
         Ok(())
     }
@@ -1774,10 +1856,7 @@ impl AdvancedE1000Driver {
         status.push(format!("Power: {:?}", self.power_state));
         
         // Check error rates
-        let rx_errors = self.stats.rx_errors.load(Ordering::Relaxed);
-        let tx_errors = self.stats.tx_errors.load(Ordering::Relaxed);
-        let rx_packets = self.stats.rx_packets.load(Ordering::Relaxed);
-        let tx_packets = self.stats.tx_packets.load(Ordering::Relaxed);
+        let NetworkStatsValues { rx_errors, tx_errors, rx_packets, tx_packets, .. } = self.stats.snapshot();
         
         if rx_packets > 0 {
             let error_rate = (rx_errors as f64 / rx_packets as f64) * 100.0;
@@ -1850,4 +1929,40 @@ mod tests {
         let mac = hw.read_mac_address();
         assert_ne!(mac, [0, 0, 0, 0, 0, 0]);
     }
+    
+    #[test]
+    fn test_ring_reconfiguration() {
+        use super::super::ring_config::reconfigure;
+        
+        let mut driver = AdvancedE1000Driver::new();
+        driver.device.device_id = 0x1026;
+        driver.detect_hardware_capabilities().unwrap();
+        driver.initialize_descriptor_rings().unwrap();
+        driver.configure_hardware().unwrap();
+        driver.link_up = true;
+        
+        let offloads = Offloads::RX_CHECKSUM | Offloads::VLAN_STRIP;
+        reconfigure(&mut driver, RingConfig { rx: 4096, tx: 512 }, offloads).unwrap();
+        assert_eq!(driver.ring_config(), (RingConfig { rx: 4096, tx: 512 }, offloads));
+        assert_eq!(driver.hardware.read_register(E1000_RDLEN), 4096 * 16);
+        assert_eq!(driver.hardware.read_register(E1000_TDLEN), 512 * 16);
+        assert_ne!(driver.hardware.read_register(E1000_RCTL) & E1000_RCTL_EN, 0);
+        assert_ne!(driver.hardware.read_register(E1000_TCTL) & E1000_TCTL_EN, 0);
+        assert_ne!(driver.hardware.read_register(E1000_RXCSUM) & E1000_RXCSUM_TUOFL, 0);
+        assert_ne!(driver.hardware.read_register(E1000_CTRL) & E1000_CTRL_VME, 0);
+        assert_eq!(driver.hardware.read_register(E1000_RCTL) & E1000_RCTL_VFE, 0);
+        assert!(driver.link_up);
+        
+        // Beyond the maxima, or not a whole number of eight descriptors
+        assert!(reconfigure(&mut driver, RingConfig { rx: 4104, tx: 512 }, offloads).is_err());
+        assert!(reconfigure(&mut driver, RingConfig { rx: 1000, tx: 512 }, offloads).is_err());
+        assert_eq!(driver.hardware.read_register(E1000_RDLEN), 4096 * 16);
+        
+        // The 82542 has smaller rings and no offloads
+        let mut driver = AdvancedE1000Driver::new();
+        driver.device.device_id = 0x1000;
+        driver.detect_hardware_capabilities().unwrap();
+        assert_eq!(driver.ring_limits().max_rx, 256);
+        assert!(reconfigure(&mut driver, RingConfig { rx: 256, tx: 256 }, Offloads::TSO).is_err());
+    }
 }
@@ -31,10 +31,34 @@ extern crate alloc;
 use orion_driver::{
     NetworkDriver, DeviceInfo, DriverError, DriverResult, OrionDriver,
     MessageLoop, ReceivedMessage, IpcInterface, MmioAccessor, MmioPermissions,
-    LinkStatus, NetworkStats, BusType,
+    LinkStatus, NetworkStats, BusType, IoRequestType,
 };
 use alloc::{vec::Vec, collections::BTreeMap, boxed::Box, string::String};
-use core::sync::atomic::{AtomicU64, Ordering};
+use orion_fwupdate::{
+    handle_control, FirmwareDevice, FirmwareInfo, FirmwareUpdater, FwError, Transport, FW_IOCTL_CONTROL,
+};
+use orion_ipc::IpcChannel;
+use orion_phy::{handle_control as handle_phy_control, mii, Mdio, PhyError, PHY_IOCTL_CONTROL};
+use orion_rxpool::pool::FLAG_CHECKSUM_VALID;
+use orion_rxpool::{shm_flags, DeliverReply, NodePool, PoolLayout, RxCompletion, RxPoolRequest, Watermarks};
+use orion_sys::clock_get;
+
+use super::ring_config::{Offloads, Reconfigurable, RingConfig, RingLimits};
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// I/O server channel, where the firmware trust anchors come from
+struct IoIpc(IpcChannel);
+
+impl Transport for IoIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
 
 // ========================================
 // ENHANCED E1000E CONSTANTS AND ENUMS
@@ -48,6 +72,7 @@ const E1000E_EERD: usize = 0x00014;      // EEPROM Read
 const E1000E_CTRL_EXT: usize = 0x00018;  // Extended Device Control
 const E1000E_FLA: usize = 0x0001C;       // Flash Access
 const E1000E_MDIC: usize = 0x00020;      // MDI Control
+const E1000E_EEWR: usize = 0x0102C;      // EEPROM Write
 const E1000E_SCTL: usize = 0x00024;      // SerDes Control
 const E1000E_FCAL: usize = 0x00028;      // Flow Control Address Low
 const E1000E_FCAH: usize = 0x0002C;      // Flow Control Address High
@@ -81,6 +106,10 @@ const E1000E_TDT: usize = 0x03818;      // Transmit Descriptor Tail
 // Enhanced address registers
 const E1000E_RAL: usize = 0x05400;      // Receive Address Low
 const E1000E_RAH: usize = 0x05404;      // Receive Address High
+const E1000E_RAH_AV: u32 = 0x80000000;  // Address valid
+const E1000E_RXCSUM: usize = 0x05000;   // Receive Checksum Control
+const E1000E_RXCSUM_IPOFL: u32 = 0x00000100; // IP checksum offload
+const E1000E_RXCSUM_TUOFL: u32 = 0x00000200; // TCP/UDP checksum offload
 
 // Enhanced control register bits
 const E1000E_CTRL_FD: u32 = 0x00000001;     // Full duplex
@@ -95,6 +124,7 @@ const E1000E_CTRL_SPD_1000: u32 = 0x00000200; // Force 1000Mb
 const E1000E_CTRL_FRCSPD: u32 = 0x00000800;   // Force Speed
 const E1000E_CTRL_FRCDPX: u32 = 0x00001000;   // Force Duplex
 const E1000E_CTRL_RST: u32 = 0x04000000;      // Global reset
+const E1000E_CTRL_VME: u32 = 0x40000000;      // VLAN mode enable (tag stripping)
 
 // Enhanced status register bits
 const E1000E_STATUS_FD: u32 = 0x00000001;     // Full duplex
@@ -124,6 +154,7 @@ const E1000E_RCTL_CFIEN: u32 = 0x00080000;   // Canonical form indicator enable
 const E1000E_RCTL_CFI: u32 = 0x00100000;     // Canonical form indicator
 const E1000E_RCTL_DPF: u32 = 0x00400000;     // Discard pause frames
 const E1000E_RCTL_PMCF: u32 = 0x00800000;    // Pass MAC control frames
+const E1000E_RCTL_BSEX: u32 = 0x02000000;    // Buffer size times 16
 const E1000E_RCTL_SECRC: u32 = 0x04000000;   // Strip ethernet CRC
 
 // Enhanced transmit control bits
@@ -137,6 +168,80 @@ const E1000E_TCTL_RTLC: u32 = 0x01000000;    // Retransmit on late collision
 const E1000E_TCTL_NRTU: u32 = 0x02000000;    // No retransmit on underrun
 const E1000E_TCTL_MULR: u32 = 0x10000000;    // Multiple request
 
+// NVM access (EERD/EEWR word interface)
+const E1000E_EECD_PRES: u32 = 0x00000100;    // NVM present
+const E1000E_EECD_SIZE_MASK: u32 = 0x00007800; // NVM size, 2^(n + 6) words
+const E1000E_EECD_SIZE_SHIFT: u32 = 11;
+const E1000E_NVM_RW_START: u32 = 0x00000001;
+const E1000E_NVM_RW_DONE: u32 = 0x00000002;
+const E1000E_NVM_RW_ADDR_SHIFT: u32 = 2;
+const E1000E_NVM_DATA_SHIFT: u32 = 16;
+const E1000E_NVM_MAX_WORDS: usize = 32 * 1024;
+
+// NVM layout
+const E1000E_NVM_MAC_WORDS: usize = 3;        // Words 0-2 hold the MAC address
+const E1000E_NVM_VERSION: usize = 0x05;       // Image version: major.minor-build
+const E1000E_NVM_CHECKSUM_WORD: usize = 0x3F; // Words 0..=0x3F sum to 0xBABA
+const E1000E_NVM_CHECKSUM: u16 = 0xBABA;
+
+const E1000E_NVM_SIGNATURE_WORD: usize = 0x13; // Bits 15:14 are 10b in a valid image
+const E1000E_NVM_SIGNATURE_MASK: u16 = 0xC000;
+const E1000E_NVM_SIGNATURE_VALID: u16 = 0x8000;
+
+/// The NVM has a single image; it is reported as slot 1
+const E1000E_NVM_SLOT: u8 = 1;
+
+// ICH/PCH flash registers, in the flash BAR
+const ICH_FLASH_GFPREG: usize = 0x0000;  // GbE flash region base and limit
+const ICH_FLASH_HSFSTS: usize = 0x0004;  // Hardware sequencing status
+const ICH_FLASH_HSFCTL: usize = 0x0006;  // Hardware sequencing control
+const ICH_FLASH_FADDR: usize = 0x0008;   // Flash linear address
+const ICH_FLASH_FDATA0: usize = 0x0010;  // Flash data
+const ICH_FLASH_REGION_MASK: u32 = 0x00001FFF; // In 4K sectors
+const ICH_FLASH_SECTOR_SIZE: usize = 4096;
+const ICH_FLASH_LINEAR_MASK: u32 = 0x00FFFFFF;
+const ICH_HSFSTS_FDONE: u16 = 0x0001;    // Cycle done
+const ICH_HSFSTS_FCERR: u16 = 0x0002;    // Cycle error
+const ICH_HSFSTS_DAEL: u16 = 0x0004;     // Access error
+const ICH_HSFSTS_FLINPRO: u16 = 0x0020;  // Cycle in progress
+const ICH_HSFCTL_FGO: u16 = 0x0001;      // Start the cycle (read)
+const ICH_HSFCTL_WORD: u16 = 0x0100;     // Two bytes (byte count - 1)
+
+// MDI control
+const E1000E_MDIC_DATA_MASK: u32 = 0x0000FFFF;
+const E1000E_MDIC_REG_SHIFT: u32 = 16;
+const E1000E_MDIC_PHY_SHIFT: u32 = 21;
+const E1000E_MDIC_OP_WRITE: u32 = 0x04000000;
+const E1000E_MDIC_OP_READ: u32 = 0x08000000;
+const E1000E_MDIC_READY: u32 = 0x10000000;
+const E1000E_MDIC_ERROR: u32 = 0x40000000;
+const E1000E_PHY_ADDRESS: u32 = 1;       // Internal PHY
+
+// Interrupt causes
+const E1000E_ICR_TXDW: u32 = 0x00000001; // Transmit descriptor written back
+const E1000E_ICR_LSC: u32 = 0x00000004;  // Link status change
+const E1000E_ICR_RXT0: u32 = 0x00000080; // Receive timer
+
+// Receive descriptor status and errors
+const E1000E_RXD_STAT_DD: u8 = 0x01;    // Descriptor done
+const E1000E_RXD_STAT_EOP: u8 = 0x02;   // End of packet
+const E1000E_RXD_STAT_TCPCS: u8 = 0x20; // TCP/UDP checksum checked
+const E1000E_RXD_ERR_TCPE: u8 = 0x20;   // TCP/UDP checksum error
+const E1000E_RXD_ERR_RXE: u8 = 0x80;    // Receive data error
+
+// Descriptor ring sizes, in eights of descriptors for the 128-byte
+// alignment of RDLEN and TDLEN
+const E1000E_MIN_RING: u16 = 64;
+const E1000E_MAX_RING: u16 = 4096;
+const E1000E_RING_ALIGN: u16 = 8;
+// Every e1000e part has these
+const E1000E_OFFLOADS: Offloads = Offloads::from_bits(
+    Offloads::RX_CHECKSUM.bits() | Offloads::TX_CHECKSUM.bits() | Offloads::TSO.bits() |
+    Offloads::VLAN_STRIP.bits() | Offloads::VLAN_FILTER.bits()
+);
+// Checks of the transmit ring while waiting for it to drain
+const E1000E_QUIESCE_POLLS: u32 = 1000;
+
 // Enhanced descriptor structures
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
@@ -195,49 +300,139 @@ pub enum EnhancedDuplexMode {
     Unknown,
 }
 
+/// GbE region of the chipset's SPI flash on ICH8 and later parts, where
+/// EERD does not reach. The region holds two banks; the one carrying a
+/// valid signature is the NVM.
+struct IchFlash {
+    mmio: MmioAccessor,
+    /// Byte address of the region in the flash
+    region: usize,
+    /// Size of a bank in words
+    bank_words: usize,
+    bank: usize,
+}
+
+impl IchFlash {
+    fn read_word(&self, word: usize) -> DriverResult<u16> {
+        let address = self.region + (self.bank * self.bank_words + word) * 2;
+        self.read_flash_word(address)
+    }
+
+    fn read_flash_word(&self, address: usize) -> DriverResult<u16> {
+        let status = self.mmio.read_u16(ICH_FLASH_HSFSTS)?;
+        if status & ICH_HSFSTS_FLINPRO != 0 {
+            return Err(DriverError::ResourceBusy);
+        }
+        // The done and error bits clear when written with 1
+        self.mmio.write_u16(ICH_FLASH_HSFSTS, ICH_HSFSTS_FDONE | ICH_HSFSTS_FCERR | ICH_HSFSTS_DAEL)?;
+        self.mmio.write_u32(ICH_FLASH_FADDR, address as u32 & ICH_FLASH_LINEAR_MASK)?;
+        self.mmio.write_u16(ICH_FLASH_HSFCTL, ICH_HSFCTL_WORD | ICH_HSFCTL_FGO)?;
+        for _ in 0..100000 {
+            let status = self.mmio.read_u16(ICH_FLASH_HSFSTS)?;
+            if status & (ICH_HSFSTS_FCERR | ICH_HSFSTS_DAEL) != 0 {
+                return Err(DriverError::IoError);
+            }
+            if status & ICH_HSFSTS_FDONE != 0 {
+                return Ok(self.mmio.read_u32(ICH_FLASH_FDATA0)? as u16);
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+}
+
+/// Where the NVM words are read from
+enum Nvm {
+    /// EEPROM or flash behind EERD/EEWR
+    Eerd,
+    IchFlash(IchFlash),
+    /// No NVM to read; the address comes from RAL0/RAH0
+    Absent,
+}
+
+/// Parts whose NVM lives in the chipset flash (ICH8, ICH9, ICH10, PCH)
+fn uses_ich_flash(device_id: u16) -> bool {
+    matches!(
+        device_id,
+        0x1049..=0x104D | 0x10BD | 0x10BF..=0x10C5 | 0x10CB..=0x10CE | 0x10DE | 0x10DF | 0x10E5
+            | 0x10EA | 0x10EB | 0x10EF | 0x10F0 | 0x10F5
+    )
+}
+
+/// An address a station can use: not multicast and not all zeros
+fn is_unicast_mac(mac: &[u8; 6]) -> bool {
+    mac[0] & 0x01 == 0 && mac.iter().any(|&byte| byte != 0)
+}
+
 // Enhanced network statistics
-#[derive(Debug, Clone, Default)]
-pub struct EnhancedNetworkStats {
-    pub rx_packets: AtomicU64,
-    pub tx_packets: AtomicU64,
-    pub rx_bytes: AtomicU64,
-    pub tx_bytes: AtomicU64,
-    pub rx_errors: AtomicU64,
-    pub tx_errors: AtomicU64,
-    pub rx_dropped: AtomicU64,
-    pub tx_dropped: AtomicU64,
-    pub rx_fifo_errors: AtomicU64,
-    pub tx_fifo_errors: AtomicU64,
-    pub rx_frame_errors: AtomicU64,
-    pub tx_carrier_errors: AtomicU64,
-    pub rx_collision_errors: AtomicU64,
-    pub tx_collision_errors: AtomicU64,
-    pub rx_crc_errors: AtomicU64,
-    pub tx_aborted_errors: AtomicU64,
-    pub rx_missed_errors: AtomicU64,
-    pub tx_window_errors: AtomicU64,
-    pub rx_length_errors: AtomicU64,
-    pub tx_heartbeat_errors: AtomicU64,
-    pub rx_overflow_errors: AtomicU64,
-    pub tx_underflow_errors: AtomicU64,
+orion_stats::counter_set! {
+    pub struct EnhancedNetworkStats => EnhancedNetworkStatsValues {
+        rx_packets,
+        tx_packets,
+        rx_bytes,
+        tx_bytes,
+        rx_errors,
+        tx_errors,
+        rx_dropped,
+        tx_dropped,
+        rx_fifo_errors,
+        tx_fifo_errors,
+        rx_frame_errors,
+        tx_carrier_errors,
+        rx_collision_errors,
+        tx_collision_errors,
+        rx_crc_errors,
+        tx_aborted_errors,
+        rx_missed_errors,
+        tx_window_errors,
+        rx_length_errors,
+        tx_heartbeat_errors,
+        rx_overflow_errors,
+        tx_underflow_errors,
+    }
+}
+
+/// Receive pool shared with the network server: receive descriptors point
+/// at its page-sized buffers, handed on by index once filled
+struct RxPoolBinding {
+    pool: NodePool,
+    id: u32,
+    mapping: u64,
+    net: IpcChannel,
+    /// Pool buffer behind each receive descriptor
+    posted: Vec<u32>,
+    /// The server asked for backpressure: buffers are not reposted
+    throttled: bool,
 }
 
 // Enhanced e1000e driver structure
 pub struct EnhancedE1000EDriver {
     device: DeviceInfo,
     mmio: MmioAccessor,
+    nvm: Nvm,
     mac_address: [u8; 6],
+    /// Address from the NVM, the one the board was built with
+    permanent_mac: [u8; 6],
     rx_descriptors: Vec<E1000ERxDesc>,
     tx_descriptors: Vec<E1000ETxDesc>,
     rx_buffer_pool: Vec<Vec<u8>>,
     tx_buffer_pool: Vec<Vec<u8>>,
+    /// Receive pool shared with the network server, which then takes
+    /// frames in place of receive_packet()
+    rx_pool: Option<RxPoolBinding>,
+    /// Next receive descriptor a pool buffer is posted to
+    rx_post: usize,
     rx_head: usize,
     rx_tail: usize,
     tx_head: usize,
     tx_tail: usize,
     rx_buffer_size: usize,
     tx_buffer_size: usize,
-    descriptor_count: usize,
+    rx_descriptor_count: usize,
+    tx_descriptor_count: usize,
+    /// Found at initialization, for ring and offload reconfiguration
+    ring_limits: RingLimits,
+    offloads: Offloads,
     stats: EnhancedNetworkStats,
     link_up: bool,
     link_speed: EnhancedLinkSpeed,
@@ -245,6 +440,9 @@ pub struct EnhancedE1000EDriver {
     interrupt_enabled: bool,
     power_management_enabled: bool,
     advanced_features_enabled: bool,
+    firmware_updater: FirmwareUpdater,
+    /// NVM contents before the last firmware write, for a rollback
+    nvm_backup: Option<Vec<u16>>,
 }
 
 impl EnhancedE1000EDriver {
@@ -295,18 +493,28 @@ impl EnhancedE1000EDriver {
         Ok(EnhancedE1000EDriver {
             device: device_info,
             mmio,
+            nvm: Nvm::Eerd,
             mac_address: [0u8; 6],
+            permanent_mac: [0u8; 6],
             rx_descriptors,
             tx_descriptors,
             rx_buffer_pool,
             tx_buffer_pool,
+            rx_pool: None,
+            rx_post: 0,
             rx_head: 0,
             rx_tail: 0,
             tx_head: 0,
             tx_tail: 0,
             rx_buffer_size,
             tx_buffer_size,
-            descriptor_count,
+            rx_descriptor_count: descriptor_count,
+            tx_descriptor_count: descriptor_count,
+            ring_limits: RingLimits::fixed(
+                RingConfig { rx: descriptor_count as u16, tx: descriptor_count as u16 },
+                Offloads::NONE,
+            ),
+            offloads: Offloads::RX_CHECKSUM | Offloads::TX_CHECKSUM | Offloads::TSO | Offloads::VLAN_FILTER,
             stats: EnhancedNetworkStats::default(),
             link_up: false,
             link_speed: EnhancedLinkSpeed::SpeedUnknown,
@@ -314,6 +522,8 @@ impl EnhancedE1000EDriver {
             interrupt_enabled: false,
             power_management_enabled: false,
             advanced_features_enabled: false,
+            firmware_updater: FirmwareUpdater::new(),
+            nvm_backup: None,
         })
     }
 
@@ -322,15 +532,29 @@ impl EnhancedE1000EDriver {
         // Reset the device
         self.reset_device()?;
         
+        // Without a receive pool frames are copied out by receive_packet()
+        let _ = self.attach_rx_pool();
+        
         // Initialize descriptors
         self.initialize_descriptors()?;
         
-        // Read MAC address
+        // Find the NVM and read the MAC address from it
+        self.detect_nvm()?;
         self.read_mac_address()?;
         
         // Configure receive and transmit
         self.configure_receive()?;
         self.configure_transmit()?;
+        self.program_offloads()?;
+        self.refill_rx_ring()?;
+        self.ring_limits = RingLimits {
+            min_rx: E1000E_MIN_RING,
+            max_rx: E1000E_MAX_RING,
+            min_tx: E1000E_MIN_RING,
+            max_tx: E1000E_MAX_RING,
+            granularity: E1000E_RING_ALIGN,
+            offloads: E1000E_OFFLOADS,
+        };
         
         // Enable interrupts
         self.enable_interrupts()?;
@@ -373,44 +597,102 @@ impl EnhancedE1000EDriver {
     /// Initialize descriptor rings
     fn initialize_descriptors(&mut self) -> DriverResult<()> {
         // Set up receive descriptor ring
-        let rx_base = self.rx_buffer_pool.as_ptr() as u64;
+        let rx_base = self.rx_descriptors.as_ptr() as u64;
         self.mmio.write_u32(E1000E_RDBAL, (rx_base & 0xFFFFFFFF) as u32)?;
         self.mmio.write_u32(E1000E_RDBAH, (rx_base >> 32) as u32)?;
-        self.mmio.write_u32(E1000E_RDLEN, (self.descriptor_count * core::mem::size_of::<E1000ERxDesc>()) as u32)?;
+        self.mmio.write_u32(E1000E_RDLEN, (self.rx_descriptor_count * core::mem::size_of::<E1000ERxDesc>()) as u32)?;
         self.mmio.write_u32(E1000E_RDH, 0)?;
-        self.mmio.write_u32(E1000E_RDT, (self.descriptor_count - 1) as u32)?;
+        // Pool buffers are posted by refill_rx_ring()
+        let rx_tail = if self.rx_pool.is_some() { 0 } else { self.rx_descriptor_count - 1 };
+        self.mmio.write_u32(E1000E_RDT, rx_tail as u32)?;
         
         // Set up transmit descriptor ring
         let tx_base = self.tx_buffer_pool.as_ptr() as u64;
         self.mmio.write_u32(E1000E_TDBAL, (tx_base & 0xFFFFFFFF) as u32)?;
         self.mmio.write_u32(E1000E_TDBAH, (tx_base >> 32) as u32)?;
-        self.mmio.write_u32(E1000E_TDLEN, (self.descriptor_count * core::mem::size_of::<E1000ETxDesc>()) as u32)?;
+        self.mmio.write_u32(E1000E_TDLEN, (self.tx_descriptor_count * core::mem::size_of::<E1000ETxDesc>()) as u32)?;
         self.mmio.write_u32(E1000E_TDH, 0)?;
         self.mmio.write_u32(E1000E_TDT, 0)?;
         
         Ok(())
     }
 
-    /// Read MAC address from device
+    /// Read the permanent MAC address and program receive address 0. An
+    /// address set through set_mac_address survives the reset of an NVM
+    /// reload.
     fn read_mac_address(&mut self) -> DriverResult<()> {
-        for i in 0..6 {
-            let offset = E1000E_RAL + (i * 4);
-            let value = self.mmio.read_u32(offset)?;
-            self.mac_address[i] = (value & 0xFF) as u8;
+        self.permanent_mac = self.read_permanent_mac()?;
+        if !is_unicast_mac(&self.mac_address) {
+            self.mac_address = self.permanent_mac;
         }
-        Ok(())
+        self.write_receive_address(self.mac_address)
+    }
+
+    /// Address from NVM words 0-2 when the image checks out, otherwise the
+    /// one the platform firmware left in RAL0/RAH0
+    fn read_permanent_mac(&mut self) -> DriverResult<[u8; 6]> {
+        if self.nvm_words()?.is_some() && matches!(self.nvm_checksum_valid(), Ok(true)) {
+            let mut mac = [0u8; 6];
+            for word in 0..E1000E_NVM_MAC_WORDS {
+                let value = self.read_nvm_word(word)?;
+                mac[word * 2..word * 2 + 2].copy_from_slice(&value.to_le_bytes());
+            }
+            // Both ports of a dual-port NIC read the same NVM; the second
+            // one uses the address with its last bit flipped
+            let function = (self.mmio.read_u32(E1000E_STATUS)? & E1000E_STATUS_FUNC_MASK) >> E1000E_STATUS_FUNC_SHIFT;
+            if function == 1 {
+                mac[5] ^= 0x01;
+            }
+            if is_unicast_mac(&mac) {
+                return Ok(mac);
+            }
+        }
+
+        let low = self.mmio.read_u32(E1000E_RAL)?;
+        let high = self.mmio.read_u32(E1000E_RAH)?;
+        let mut mac = [0u8; 6];
+        mac[..4].copy_from_slice(&low.to_le_bytes());
+        mac[4..].copy_from_slice(&(high as u16).to_le_bytes());
+        if high & E1000E_RAH_AV != 0 && is_unicast_mac(&mac) {
+            Ok(mac)
+        } else {
+            Err(DriverError::InvalidData)
+        }
+    }
+
+    /// Program receive address 0, the station address
+    fn write_receive_address(&mut self, mac: [u8; 6]) -> DriverResult<()> {
+        let low = u32::from_le_bytes([mac[0], mac[1], mac[2], mac[3]]);
+        let high = u16::from_le_bytes([mac[4], mac[5]]) as u32;
+        self.mmio.write_u32(E1000E_RAL, low)?;
+        self.mmio.write_u32(E1000E_RAH, high | E1000E_RAH_AV)
+    }
+
+    /// Address the board was built with, whatever set_mac_address did
+    pub fn permanent_mac_address(&self) -> [u8; 6] {
+        self.permanent_mac
     }
 
     /// Configure receive functionality
     fn configure_receive(&mut self) -> DriverResult<()> {
         let mut rctl = self.mmio.read_u32(E1000E_RCTL)?;
-        rctl |= E1000E_RCTL_EN | E1000E_RCTL_SBP | E1000E_RCTL_UPE | E1000E_RCTL_MPE;
+        // Unicast frames go through receive address 0, which holds our address
+        rctl |= E1000E_RCTL_EN | E1000E_RCTL_SBP | E1000E_RCTL_MPE;
         rctl |= E1000E_RCTL_LPE | E1000E_RCTL_BAM | E1000E_RCTL_VFE;
-        rctl &= !E1000E_RCTL_BSIZE;
-        rctl |= E1000E_RCTL_BSIZE & 0x00030000; // 2048 byte buffers
         self.mmio.write_u32(E1000E_RCTL, rctl)?;
         
-        Ok(())
+        self.program_buffer_size()
+    }
+
+    /// Program the receive buffer size: a page with a receive pool
+    fn program_buffer_size(&mut self) -> DriverResult<()> {
+        let mut rctl = self.mmio.read_u32(E1000E_RCTL)? & !(E1000E_RCTL_BSIZE | E1000E_RCTL_BSEX);
+        if self.rx_pool.is_some() {
+            rctl |= E1000E_RCTL_BSIZE | E1000E_RCTL_BSEX; // 4096 byte buffers
+        } else {
+            rctl |= E1000E_RCTL_BSIZE & 0x00030000; // 2048 byte buffers
+        }
+        self.mmio.write_u32(E1000E_RCTL, rctl)
     }
 
     /// Configure transmit functionality
@@ -429,14 +711,36 @@ impl EnhancedE1000EDriver {
 
     /// Enable interrupts
     fn enable_interrupts(&mut self) -> DriverResult<()> {
-        // Enable receive and transmit interrupts
-        let ims = 0x00000004 | 0x00000008; // RX and TX interrupts
+        // Enable receive, transmit and link status change interrupts
+        let ims = E1000E_ICR_RXT0 | E1000E_ICR_TXDW | E1000E_ICR_LSC;
         self.mmio.write_u32(E1000E_IMS, ims)?;
         
         self.interrupt_enabled = true;
         Ok(())
     }
 
+    /// Program the receive offloads; transmit ones are asked for frame by
+    /// frame in the descriptors
+    fn program_offloads(&mut self) -> DriverResult<()> {
+        let mut rxcsum = self.mmio.read_u32(E1000E_RXCSUM)? & !(E1000E_RXCSUM_IPOFL | E1000E_RXCSUM_TUOFL);
+        if self.offloads.contains(Offloads::RX_CHECKSUM) {
+            rxcsum |= E1000E_RXCSUM_IPOFL | E1000E_RXCSUM_TUOFL;
+        }
+        self.mmio.write_u32(E1000E_RXCSUM, rxcsum)?;
+        
+        let mut ctrl = self.mmio.read_u32(E1000E_CTRL)? & !E1000E_CTRL_VME;
+        if self.offloads.contains(Offloads::VLAN_STRIP) {
+            ctrl |= E1000E_CTRL_VME;
+        }
+        self.mmio.write_u32(E1000E_CTRL, ctrl)?;
+        
+        let mut rctl = self.mmio.read_u32(E1000E_RCTL)? & !E1000E_RCTL_VFE;
+        if self.offloads.contains(Offloads::VLAN_FILTER) {
+            rctl |= E1000E_RCTL_VFE;
+        }
+        self.mmio.write_u32(E1000E_RCTL, rctl)
+    }
+
     /// Start the device
     fn start_device(&mut self) -> DriverResult<()> {
         // Enable receive and transmit
@@ -456,6 +760,27 @@ impl EnhancedE1000EDriver {
         let status = self.mmio.read_u32(E1000E_STATUS)?;
         
         self.link_up = (status & E1000E_STATUS_LU) != 0;
+        if !self.link_up {
+            self.link_speed = EnhancedLinkSpeed::SpeedUnknown;
+            self.duplex_mode = EnhancedDuplexMode::Unknown;
+            return Ok(());
+        }
+
+        // What the PHY resolved with its link partner; STATUS is left for
+        // forced links and PHYs that do not answer on MDIC
+        if let Ok(Some(mode)) = mii::link_mode(self) {
+            self.link_speed = match mode.speed_mbps {
+                1000 => EnhancedLinkSpeed::Speed1000Mbps,
+                100 => EnhancedLinkSpeed::Speed100Mbps,
+                _ => EnhancedLinkSpeed::Speed10Mbps,
+            };
+            self.duplex_mode = if mode.full_duplex {
+                EnhancedDuplexMode::FullDuplex
+            } else {
+                EnhancedDuplexMode::HalfDuplex
+            };
+            return Ok(());
+        }
         
         // Determine link speed
         let speed_bits = status & E1000E_STATUS_SPEED_MASK;
@@ -476,6 +801,30 @@ impl EnhancedE1000EDriver {
         Ok(())
     }
 
+    /// Run an MDIC cycle on the internal PHY and return the register value
+    fn mdic(&mut self, command: u32) -> Result<u16, PhyError> {
+        self.mmio.write_u32(E1000E_MDIC, command).map_err(|_| PhyError::Bus)?;
+        for _ in 0..100000 {
+            let mdic = self.mmio.read_u32(E1000E_MDIC).map_err(|_| PhyError::Bus)?;
+            if mdic & E1000E_MDIC_READY != 0 {
+                if mdic & E1000E_MDIC_ERROR != 0 {
+                    return Err(PhyError::Bus);
+                }
+                return Ok((mdic & E1000E_MDIC_DATA_MASK) as u16);
+            }
+            core::hint::spin_loop();
+        }
+        Err(PhyError::Bus)
+    }
+
+    /// Serve a PHY control request (see orion_phy::handle_control); the
+    /// link is checked again as the request may have renegotiated it
+    pub fn phy_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let reply = handle_phy_control(self, request);
+        let _ = self.check_link_status();
+        reply
+    }
+
     /// Get enhanced link status
     pub fn get_enhanced_link_status(&self) -> (bool, EnhancedLinkSpeed, EnhancedDuplexMode) {
         (self.link_up, self.link_speed, self.duplex_mode)
@@ -521,6 +870,8 @@ impl OrionDriver for EnhancedE1000EDriver {
     
     fn init(&mut self, device: DeviceInfo) -> DriverResult<()> {
         self.device = device;
+        // Without trust anchors every firmware image is refused
+        let _ = self.firmware_updater.load_trust_anchors(&mut IoIpc(IpcChannel::connect("io")));
         self.initialize()
     }
     
@@ -528,16 +879,21 @@ impl OrionDriver for EnhancedE1000EDriver {
         // Read interrupt cause
         let icr = self.mmio.read_u32(E1000E_ICR)?;
         
-        if icr & 0x00000004 != 0 {
+        if icr & E1000E_ICR_RXT0 != 0 {
             // Receive interrupt
             self.handle_receive_interrupt()?;
         }
         
-        if icr & 0x00000008 != 0 {
+        if icr & E1000E_ICR_TXDW != 0 {
             // Transmit interrupt
             self.handle_transmit_interrupt()?;
         }
         
+        if icr & E1000E_ICR_LSC != 0 {
+            // Link went up or down, or renegotiated
+            self.check_link_status()?;
+        }
+        
         Ok(())
     }
     
@@ -554,12 +910,41 @@ impl OrionDriver for EnhancedE1000EDriver {
         tctl &= !E1000E_TCTL_EN;
         self.mmio.write_u32(E1000E_TCTL, tctl)?;
         
+        self.detach_rx_pool();
         Ok(())
     }
     
-    fn handle_message(&mut self, _message: ReceivedMessage, _ipc: &mut dyn IpcInterface) -> DriverResult<()> {
-        // Handle driver-specific messages
-        Ok(())
+    fn handle_message(&mut self, message: ReceivedMessage, ipc: &mut dyn IpcInterface) -> DriverResult<()> {
+        self.check_firmware_deadline();
+        match message {
+            ReceivedMessage::IoRequest(io_msg)
+                if matches!(io_msg.request_type, IoRequestType::Ioctl) && io_msg.length == FW_IOCTL_CONTROL =>
+            {
+                // Firmware requests answer with data rather than a length
+                let reply = self.firmware_control(&io_msg.data);
+                ipc.send_response(io_msg.header.sequence, 0, &reply)
+            }
+            ReceivedMessage::IoRequest(io_msg)
+                if matches!(io_msg.request_type, IoRequestType::Ioctl) && io_msg.length == PHY_IOCTL_CONTROL =>
+            {
+                let reply = self.phy_control(&io_msg.data);
+                ipc.send_response(io_msg.header.sequence, 0, &reply)
+            }
+            // Handle driver-specific messages
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Mdio for EnhancedE1000EDriver {
+    fn read(&mut self, register: u8) -> Result<u16, PhyError> {
+        let command = ((register as u32) << E1000E_MDIC_REG_SHIFT) | (E1000E_PHY_ADDRESS << E1000E_MDIC_PHY_SHIFT);
+        self.mdic(command | E1000E_MDIC_OP_READ)
+    }
+
+    fn write(&mut self, register: u8, value: u16) -> Result<(), PhyError> {
+        let command = ((register as u32) << E1000E_MDIC_REG_SHIFT) | (E1000E_PHY_ADDRESS << E1000E_MDIC_PHY_SHIFT);
+        self.mdic(command | E1000E_MDIC_OP_WRITE | value as u32).map(|_| ())
     }
 }
 
@@ -575,7 +960,7 @@ impl NetworkDriver for EnhancedE1000EDriver {
         }
         
         // Get next transmit descriptor
-        let next_tx = (self.tx_head + 1) % self.descriptor_count;
+        let next_tx = (self.tx_head + 1) % self.tx_descriptor_count;
         if next_tx == self.tx_tail {
             return Err(DriverError::NoResources);
         }
@@ -596,8 +981,8 @@ impl NetworkDriver for EnhancedE1000EDriver {
         self.mmio.write_u32(E1000E_TDH, self.tx_head as u32)?;
         
         // Update statistics
-        self.stats.tx_packets.fetch_add(1, Ordering::Relaxed);
-        self.stats.tx_bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+        self.stats.tx_packets().inc();
+        self.stats.tx_bytes().add(data.len() as u64);
         
         Ok(data.len())
     }
@@ -607,6 +992,15 @@ impl NetworkDriver for EnhancedE1000EDriver {
             return Err(DriverError::DeviceNotReady);
         }
         
+        // Frames go to the network server through the receive pool; polls
+        // keep ringing a throttled server so reception resumes with it
+        if let Some(binding) = self.rx_pool.as_ref() {
+            if binding.throttled {
+                self.deliver_rx_buffers()?;
+            }
+            return Err(DriverError::NoData);
+        }
+        
         // Check if we have received packets
         let status = self.rx_descriptors[self.rx_tail].status;
         if status & 0x01 == 0 {
@@ -624,37 +1018,114 @@ impl NetworkDriver for EnhancedE1000EDriver {
         buffer[..length].copy_from_slice(&rx_buffer[..length]);
         
         // Update tail pointer
-        self.rx_tail = (self.rx_tail + 1) % self.descriptor_count;
+        self.rx_tail = (self.rx_tail + 1) % self.rx_descriptor_count;
         self.mmio.write_u32(E1000E_RDT, self.rx_tail as u32)?;
         
         // Update statistics
-        self.stats.rx_packets.fetch_add(1, Ordering::Relaxed);
-        self.stats.rx_bytes.fetch_add(length as u64, Ordering::Relaxed);
+        self.stats.rx_packets().inc();
+        self.stats.rx_bytes().add(length as u64);
         
         Ok(length)
     }
     
+    fn link_status(&self) -> LinkStatus {
+        let speed_mbps = match self.link_speed {
+            EnhancedLinkSpeed::Speed10Mbps => 10,
+            EnhancedLinkSpeed::Speed100Mbps => 100,
+            EnhancedLinkSpeed::Speed1000Mbps => 1000,
+            _ => return LinkStatus::Down,
+        };
+        if !self.link_up {
+            return LinkStatus::Down;
+        }
+        LinkStatus::Up { speed_mbps, duplex: self.duplex_mode == EnhancedDuplexMode::FullDuplex }
+    }
+    
     fn get_mac_address(&self) -> DriverResult<[u8; 6]> {
         Ok(self.mac_address)
     }
     
     fn set_mac_address(&mut self, mac: [u8; 6]) -> DriverResult<()> {
-        // Update internal MAC address
+        if !is_unicast_mac(&mac) {
+            return Err(DriverError::InvalidParameter);
+        }
+        self.write_receive_address(mac)?;
         self.mac_address = mac;
+        Ok(())
+    }
+}
+
+impl Reconfigurable for EnhancedE1000EDriver {
+    fn ring_limits(&self) -> RingLimits {
+        self.ring_limits
+    }
+    
+    fn ring_config(&self) -> (RingConfig, Offloads) {
+        let rings = RingConfig { rx: self.rx_descriptor_count as u16, tx: self.tx_descriptor_count as u16 };
+        (rings, self.offloads)
+    }
+    
+    fn quiesce(&mut self) -> DriverResult<()> {
+        // The device is done with the transmit ring when its head has
+        // caught up with the tail
+        let mut polls = 0;
+        while self.mmio.read_u32(E1000E_TDH)? != self.mmio.read_u32(E1000E_TDT)? {
+            if polls == E1000E_QUIESCE_POLLS {
+                return Err(DriverError::Timeout);
+            }
+            polls += 1;
+            for _ in 0..1000 { core::hint::spin_loop(); }
+        }
         
-        // Update device registers
-        for i in 0..6 {
-            let offset = E1000E_RAL + (i * 4);
-            self.mmio.write_u32(offset, mac[i] as u32)?;
+        // Link status changes still interrupt
+        self.mmio.write_u32(E1000E_IMC, E1000E_ICR_RXT0 | E1000E_ICR_TXDW)?;
+        let rctl = self.mmio.read_u32(E1000E_RCTL)?;
+        self.mmio.write_u32(E1000E_RCTL, rctl & !E1000E_RCTL_EN)?;
+        let tctl = self.mmio.read_u32(E1000E_TCTL)?;
+        self.mmio.write_u32(E1000E_TCTL, tctl & !E1000E_TCTL_EN)
+    }
+    
+    fn reprogram(&mut self, rings: RingConfig, offloads: Offloads) -> DriverResult<()> {
+        // The buffers posted on the old ring go with the pool, and the new
+        // ring gets a pool of its size
+        let pooled = self.rx_pool.is_some();
+        self.detach_rx_pool();
+        let rx_desc = E1000ERxDesc { addr: 0, length: 0, csum: 0, status: 0, errors: 0, special: 0 };
+        let tx_desc = E1000ETxDesc { addr: 0, length: 0, cso: 0, cmd: 0, status: 0, css: 0, special: 0 };
+        self.rx_descriptor_count = rings.rx as usize;
+        self.tx_descriptor_count = rings.tx as usize;
+        self.rx_descriptors = vec![rx_desc; self.rx_descriptor_count];
+        self.tx_descriptors = vec![tx_desc; self.tx_descriptor_count];
+        self.rx_buffer_pool = vec![vec![0u8; self.rx_buffer_size]; self.rx_descriptor_count];
+        self.tx_buffer_pool = vec![vec![0u8; self.tx_buffer_size]; self.tx_descriptor_count];
+        self.rx_head = 0;
+        self.rx_tail = 0;
+        self.tx_head = 0;
+        self.tx_tail = 0;
+        if pooled {
+            let _ = self.attach_rx_pool();
         }
+        self.initialize_descriptors()?;
+        self.program_buffer_size()?;
+        self.refill_rx_ring()?;
         
-        Ok(())
+        self.offloads = offloads;
+        self.program_offloads()
+    }
+    
+    fn resume(&mut self) -> DriverResult<()> {
+        // Receive filters and the station address were left as they were
+        self.start_device()?;
+        self.enable_interrupts()
     }
 }
 
 impl EnhancedE1000EDriver {
     /// Handle receive interrupt
     fn handle_receive_interrupt(&mut self) -> DriverResult<()> {
+        if self.rx_pool.is_some() {
+            return self.deliver_rx_buffers();
+        }
         // Process received packets
         // This would typically involve checking descriptors and processing packets
         Ok(())
@@ -668,6 +1139,378 @@ impl EnhancedE1000EDriver {
     }
 }
 
+// ========================================
+// RECEIVE POOL
+// ========================================
+
+impl EnhancedE1000EDriver {
+    /// Lay out a pool of page-sized buffers, one per receive descriptor,
+    /// in shared memory and register it with the network server
+    fn attach_rx_pool(&mut self) -> DriverResult<()> {
+        if self.rx_pool.is_some() {
+            return Ok(());
+        }
+        // The device never fills the descriptor at the tail, so one less
+        // than the ring is posted at most
+        let descriptors = self.rx_descriptor_count as u32;
+        let layout = PoolLayout::page_sized(descriptors.next_power_of_two())
+            .map_err(|_| DriverError::InitializationFailed)?;
+        // PCI enumeration reports no NUMA node yet: the pool goes anywhere
+        let node = None;
+        let memory = orion_sys::shm_create(layout.region_size(), shm_flags(node))
+            .map_err(|_| DriverError::MemoryError)?;
+        let (mapping, size) = orion_sys::shm_attach(memory, 0, 0).map_err(|_| DriverError::MemoryError)?;
+        
+        let watermarks = Watermarks::for_ring(descriptors - 1);
+        let pool = unsafe { NodePool::init(mapping as *mut u8, size, layout, node, watermarks) };
+        let mut net = IpcChannel::connect("net");
+        let registered = pool.ok().and_then(|pool| {
+            let reply = net.call(&RxPoolRequest::Register { memory }.encode()).ok()?;
+            if reply.len() < 8 || reply[..4] != 0i32.to_le_bytes() {
+                return None;
+            }
+            Some((pool, u32::from_le_bytes([reply[4], reply[5], reply[6], reply[7]])))
+        });
+        match registered {
+            Some((pool, id)) => {
+                self.rx_pool = Some(RxPoolBinding {
+                    pool,
+                    id,
+                    mapping,
+                    net,
+                    posted: vec![0; self.rx_descriptor_count],
+                    throttled: false,
+                });
+                self.rx_post = 0;
+                Ok(())
+            }
+            None => {
+                let _ = orion_sys::shm_detach(mapping);
+                Err(DriverError::NoResources)
+            }
+        }
+    }
+    
+    /// Unregister the receive pool; receive DMA must be stopped
+    fn detach_rx_pool(&mut self) {
+        if let Some(mut binding) = self.rx_pool.take() {
+            let _ = binding.net.call(&RxPoolRequest::Unregister { pool: binding.id }.encode());
+            let _ = orion_sys::shm_detach(binding.mapping);
+        }
+    }
+    
+    /// Post free pool buffers on the receive ring once it runs below the
+    /// low watermark, and move the tail past them
+    fn refill_rx_ring(&mut self) -> DriverResult<()> {
+        let Some(binding) = self.rx_pool.as_mut() else {
+            return Ok(());
+        };
+        
+        let count = self.rx_descriptor_count;
+        let descriptors = &mut self.rx_descriptors;
+        let rx_post = &mut self.rx_post;
+        let rx_tail = self.rx_tail;
+        let posted_buffers = &mut binding.posted;
+        let posted = binding.pool.refill(|buffer, address| {
+            let next = (*rx_post + 1) % count;
+            if next == rx_tail {
+                return false;
+            }
+            descriptors[*rx_post] = E1000ERxDesc {
+                addr: address as u64,
+                length: 0,
+                csum: 0,
+                status: 0,
+                errors: 0,
+                special: 0,
+            };
+            posted_buffers[*rx_post] = buffer;
+            *rx_post = next;
+            true
+        }).map_err(|_| DriverError::General)?;
+        
+        if posted > 0 {
+            self.mmio.write_u32(E1000E_RDT, self.rx_post as u32)?;
+        }
+        Ok(())
+    }
+    
+    /// Pass filled pool buffers to the network server by index and repost
+    /// the buffers it recycled
+    fn deliver_rx_buffers(&mut self) -> DriverResult<()> {
+        let Some(binding) = self.rx_pool.as_mut() else {
+            return Ok(());
+        };
+        
+        while self.rx_tail != self.rx_post {
+            let desc = self.rx_descriptors[self.rx_tail];
+            if desc.status & E1000E_RXD_STAT_DD == 0 {
+                break;
+            }
+            let mut completion = RxCompletion { buffer: binding.posted[self.rx_tail], ..RxCompletion::default() };
+            if desc.errors & E1000E_RXD_ERR_RXE != 0 || desc.status & E1000E_RXD_STAT_EOP == 0 {
+                // Handed back unused: frames larger than a page are not
+                // chained across descriptors
+                self.stats.rx_errors().inc();
+            } else {
+                completion.length = desc.length;
+                if self.offloads.contains(Offloads::RX_CHECKSUM)
+                    && desc.status & E1000E_RXD_STAT_TCPCS != 0
+                    && desc.errors & E1000E_RXD_ERR_TCPE == 0
+                {
+                    completion.flags |= FLAG_CHECKSUM_VALID;
+                }
+                self.stats.rx_packets().inc();
+                self.stats.rx_bytes().add(desc.length as u64);
+            }
+            binding.pool.complete(&completion).map_err(|_| DriverError::General)?;
+            self.rx_tail = (self.rx_tail + 1) % self.rx_descriptor_count;
+        }
+        
+        // The server has recycled every delivered buffer when it replies,
+        // unless socket memory is under pressure; frames left pending go
+        // with the next doorbell
+        if binding.pool.pool().pending() > 0 || binding.throttled {
+            let reply = binding.net.call(&RxPoolRequest::Deliver { pool: binding.id }.encode());
+            if let Some(reply) = reply.ok().and_then(|reply| DeliverReply::decode(&reply)) {
+                binding.throttled = reply.backpressure;
+            }
+        }
+        
+        // Backpressure: posting nothing lets the device run out of buffers
+        // instead of queueing frames the server cannot take
+        if binding.throttled {
+            return Ok(());
+        }
+        self.refill_rx_ring()
+    }
+}
+
+// ========================================
+// NVM FIRMWARE UPDATE
+// ========================================
+
+impl EnhancedE1000EDriver {
+    /// Find where the NVM is read from: EERD, or the valid bank of the
+    /// chipset flash on ICH/PCH parts
+    fn detect_nvm(&mut self) -> DriverResult<()> {
+        if !uses_ich_flash(self.device.device_id) {
+            self.nvm = Nvm::Eerd;
+            return Ok(());
+        }
+        self.nvm = Nvm::Absent;
+        if self.device.bars[1] == 0 {
+            return Ok(());
+        }
+        let mmio = unsafe {
+            MmioAccessor::new(
+                self.device.bars[1],
+                4096,
+                MmioPermissions::READ | MmioPermissions::WRITE | MmioPermissions::UNCACHED
+            )
+        };
+        let gfpreg = mmio.read_u32(ICH_FLASH_GFPREG)?;
+        let base = (gfpreg & ICH_FLASH_REGION_MASK) as usize;
+        let limit = ((gfpreg >> 16) & ICH_FLASH_REGION_MASK) as usize;
+        if limit < base {
+            return Ok(());
+        }
+        let region_bytes = (limit + 1 - base) * ICH_FLASH_SECTOR_SIZE;
+        let mut flash = IchFlash {
+            mmio,
+            region: base * ICH_FLASH_SECTOR_SIZE,
+            // Two banks of 16-bit words
+            bank_words: region_bytes / 4,
+            bank: 0,
+        };
+        for bank in 0..2 {
+            flash.bank = bank;
+            let signature = flash.read_word(E1000E_NVM_SIGNATURE_WORD)?;
+            if signature & E1000E_NVM_SIGNATURE_MASK == E1000E_NVM_SIGNATURE_VALID {
+                self.nvm = Nvm::IchFlash(flash);
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of the NVM in 16-bit words, None without an NVM
+    fn nvm_words(&mut self) -> DriverResult<Option<usize>> {
+        match &self.nvm {
+            Nvm::Eerd => {}
+            Nvm::IchFlash(flash) => return Ok(Some(flash.bank_words)),
+            Nvm::Absent => return Ok(None),
+        }
+        let eecd = self.mmio.read_u32(E1000E_EECD)?;
+        if eecd & E1000E_EECD_PRES == 0 {
+            return Ok(None);
+        }
+        let size = (eecd & E1000E_EECD_SIZE_MASK) >> E1000E_EECD_SIZE_SHIFT;
+        Ok(Some((1usize << (size + 6)).min(E1000E_NVM_MAX_WORDS)))
+    }
+
+    /// Start an EERD/EEWR access and wait for the device to finish it
+    fn nvm_access(&mut self, register: usize, value: u32) -> DriverResult<u32> {
+        self.mmio.write_u32(register, value | E1000E_NVM_RW_START)?;
+        for _ in 0..100000 {
+            let status = self.mmio.read_u32(register)?;
+            if status & E1000E_NVM_RW_DONE != 0 {
+                return Ok(status);
+            }
+            core::hint::spin_loop();
+        }
+        Err(DriverError::Timeout)
+    }
+
+    fn read_nvm_word(&mut self, word: usize) -> DriverResult<u16> {
+        match &self.nvm {
+            Nvm::Eerd => {}
+            Nvm::IchFlash(flash) => return flash.read_word(word),
+            Nvm::Absent => return Err(DriverError::Unsupported),
+        }
+        let status = self.nvm_access(E1000E_EERD, (word as u32) << E1000E_NVM_RW_ADDR_SHIFT)?;
+        Ok((status >> E1000E_NVM_DATA_SHIFT) as u16)
+    }
+
+    /// Writes go through EEWR only; the chipset flash needs erase cycles
+    /// this driver does not do
+    fn write_nvm_word(&mut self, word: usize, data: u16) -> DriverResult<()> {
+        if !matches!(self.nvm, Nvm::Eerd) {
+            return Err(DriverError::Unsupported);
+        }
+        let value = ((data as u32) << E1000E_NVM_DATA_SHIFT) | ((word as u32) << E1000E_NVM_RW_ADDR_SHIFT);
+        self.nvm_access(E1000E_EEWR, value).map(|_| ())
+    }
+
+    fn read_nvm(&mut self, words: usize) -> DriverResult<Vec<u16>> {
+        (0..words).map(|word| self.read_nvm_word(word)).collect()
+    }
+
+    /// Write the words that differ from `current`, then check them back
+    fn write_nvm(&mut self, current: &[u16], image: &[u16]) -> Result<(), FwError> {
+        for (word, (&old, &new)) in current.iter().zip(image).enumerate() {
+            if old != new {
+                self.write_nvm_word(word, new).map_err(|_| FwError::Device)?;
+            }
+        }
+        match self.read_nvm(image.len()) {
+            Ok(written) if written == image => Ok(()),
+            _ => Err(FwError::Device),
+        }
+    }
+
+    /// Words 0..=0x3F of an intact image sum to 0xBABA
+    fn nvm_checksum_valid(&mut self) -> DriverResult<bool> {
+        let checksum = (0..=E1000E_NVM_CHECKSUM_WORD)
+            .map(|word| self.read_nvm_word(word))
+            .try_fold(0u16, |sum, word| word.map(|word| sum.wrapping_add(word)))?;
+        Ok(checksum == E1000E_NVM_CHECKSUM)
+    }
+
+    /// Reset the device so it reloads its configuration from the NVM
+    fn reload_nvm(&mut self) -> Result<(), FwError> {
+        self.initialize().map_err(|_| FwError::Device)?;
+        match self.nvm_checksum_valid() {
+            Ok(true) => Ok(()),
+            _ => Err(FwError::Device),
+        }
+    }
+
+    /// Roll back an update left unconfirmed past its deadline
+    fn check_firmware_deadline(&mut self) {
+        let mut updater = core::mem::take(&mut self.firmware_updater);
+        let _ = updater.tick(self, monotonic_ns());
+        self.firmware_updater = updater;
+    }
+
+    /// Serve a firmware control request (see orion_fwupdate::handle_control)
+    pub fn firmware_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let mut updater = core::mem::take(&mut self.firmware_updater);
+        let reply = handle_control(&mut updater, self, request, monotonic_ns());
+        self.firmware_updater = updater;
+        reply
+    }
+}
+
+impl FirmwareDevice for EnhancedE1000EDriver {
+    fn identity(&self) -> (u16, u16) {
+        (self.device.vendor_id, self.device.device_id)
+    }
+
+    fn firmware_info(&mut self) -> Result<FirmwareInfo, FwError> {
+        let words = self.nvm_words().map_err(|_| FwError::Device)?.ok_or(FwError::Unsupported)?;
+        let raw = self.read_nvm_word(E1000E_NVM_VERSION).map_err(|_| FwError::Device)?;
+        let text = alloc::format!("{}.{}-{}", raw >> 12, (raw >> 4) & 0xFF, raw & 0xF);
+        let mut version = [0u8; 16];
+        version[..text.len()].copy_from_slice(text.as_bytes());
+
+        // A single image: the previous one is kept in memory for a rollback
+        Ok(FirmwareInfo {
+            version,
+            active_slot: E1000E_NVM_SLOT,
+            staging_slot: E1000E_NVM_SLOT,
+            can_rollback: true,
+            max_size: (words * 2) as u32,
+        })
+    }
+
+    fn write_slot(&mut self, slot: u8, payload: &[u8]) -> Result<(), FwError> {
+        let words = self.nvm_words().map_err(|_| FwError::Device)?.ok_or(FwError::Unsupported)?;
+        if !matches!(self.nvm, Nvm::Eerd) {
+            return Err(FwError::Unsupported);
+        }
+        if slot != E1000E_NVM_SLOT || payload.len() % 2 != 0 || payload.len() / 2 <= E1000E_NVM_CHECKSUM_WORD {
+            return Err(FwError::InvalidArgument);
+        }
+        if payload.len() / 2 > words {
+            return Err(FwError::TooLarge);
+        }
+
+        let current = self.read_nvm(payload.len() / 2).map_err(|_| FwError::Device)?;
+        let mut image: Vec<u16> = payload.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        // Keep the board's own MAC address and fix the checksum to match
+        image[..E1000E_NVM_MAC_WORDS].copy_from_slice(&current[..E1000E_NVM_MAC_WORDS]);
+        let sum = image[..E1000E_NVM_CHECKSUM_WORD].iter().fold(0u16, |sum, &word| sum.wrapping_add(word));
+        image[E1000E_NVM_CHECKSUM_WORD] = E1000E_NVM_CHECKSUM.wrapping_sub(sum);
+
+        self.nvm_backup = Some(current.clone());
+        if let Err(error) = self.write_nvm(&current, &image) {
+            // Leave the NVM as it was rather than half written
+            let _ = self.write_nvm(&image, &current);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn activate_slot(&mut self, _slot: u8) -> Result<(), FwError> {
+        self.reload_nvm()
+    }
+
+    fn restore_slot(&mut self, _slot: u8) -> Result<(), FwError> {
+        let backup = self.nvm_backup.take().ok_or(FwError::NoRollback)?;
+        let current = self.read_nvm(backup.len()).map_err(|_| FwError::Device)?;
+        self.write_nvm(&current, &backup)?;
+        self.reload_nvm()
+    }
+
+    /// NVM contents as little-endian words, for the update tool to save
+    /// before writing an image
+    fn read_image(&mut self, offset: u32, length: u32) -> Result<Vec<u8>, FwError> {
+        let words = self.nvm_words().map_err(|_| FwError::Device)?.ok_or(FwError::Unsupported)?;
+        let start = offset as usize;
+        if start > words * 2 {
+            return Err(FwError::InvalidArgument);
+        }
+        let end = (words * 2).min(start + length as usize);
+        let mut bytes = Vec::with_capacity(end - start + 2);
+        for word in start / 2..(end + 1) / 2 {
+            let value = self.read_nvm_word(word).map_err(|_| FwError::Device)?;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        Ok(bytes[start % 2..start % 2 + (end - start)].to_vec())
+    }
+}
+
 // Main function for the driver
 #[no_mangle]
 pub extern "C" fn main() -> i32 {
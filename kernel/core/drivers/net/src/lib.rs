@@ -17,6 +17,8 @@ pub mod e1000e;
 pub mod rtl8139;
 pub mod virtio_net;
 pub mod network_manager;
+pub mod link_events;
+pub mod ring_config;
 
 // Re-export main driver types for easy access
 pub use e1000::AdvancedE1000Driver;
@@ -31,6 +33,20 @@ pub use network_manager::{
     AggregatedNetworkStats,
     NetworkConfiguration,
 };
+pub use link_events::{
+    LinkEvent,
+    LinkMode,
+    LinkMonitor,
+    LinkSubscribers,
+    LINK_IOCTL_CONTROL,
+};
+pub use ring_config::{
+    Offloads,
+    Reconfigurable,
+    RingConfig,
+    RingError,
+    RingLimits,
+};
 
 // Re-export driver traits
 pub use orion_driver::{
@@ -40,7 +56,6 @@ pub use orion_driver::{
     DriverError,
     DriverResult,
     LinkStatus,
-    NetworkStats,
     BusType,
     MmioAccessor,
     MmioPermissions,
@@ -49,6 +64,25 @@ pub use orion_driver::{
     IpcInterface,
 };
 
+// Re-export the statistics types shared by all network drivers
+pub use orion_netstats::{
+    ErrorKind,
+    NetworkRates,
+    NetworkStats,
+    StatsDelta,
+};
+
+// Re-export the early packet filter run in driver receive paths
+pub use orion_pktfilter::{
+    Action as FilterAction,
+    FilterProgram,
+    PacketFilter,
+    Rule as FilterRule,
+    FILTER_IOCTL_CONTROL,
+};
+
+use core::fmt::{self, Write as _};
+
 // Version information
 pub const VERSION: &str = "2.0.0";
 pub const AUTHOR: &str = "Jeremy Noverraz <jeremy@orion-os.dev>";
@@ -155,7 +189,8 @@ pub fn driver_supports_feature(driver_name: &str, feature: &str) -> bool {
     }
 }
 
-/// Get the maximum supported speed for a driver
+/// Get the maximum supported speed for a driver. This is what the
+/// hardware can do; the speed a link runs at is in the driver's link_status()
 pub fn get_max_speed(driver_name: &str) -> Option<u32> {
     match driver_name {
         "e1000" | "e1000e" | "rtl8169" => Some(1000), // 1 Gbps
@@ -172,7 +207,8 @@ pub fn get_max_mtu(driver_name: &str) -> Option<u16> {
     }
 }
 
-/// Get the default ring size for a driver
+/// Get the default ring size for a driver. NetworkDriverManager::configure
+/// changes it on a running interface
 pub fn get_default_ring_size(driver_name: &str) -> Option<usize> {
     match driver_name {
         "e1000" | "e1000e" | "rtl8169" | "virtio_net" => Some(256),
@@ -180,7 +216,8 @@ pub fn get_default_ring_size(driver_name: &str) -> Option<usize> {
     }
 }
 
-/// Get the maximum ring size for a driver
+/// Get the maximum ring size for a driver family; what a probed device
+/// accepts is in its driver's ring_limits()
 pub fn get_max_ring_size(driver_name: &str) -> Option<usize> {
     match driver_name {
         "e1000" | "e1000e" | "rtl8169" | "virtio_net" => Some(1024),
@@ -274,89 +311,179 @@ pub fn get_supported_diagnostic_features(driver_name: &str) -> Option<&'static [
 /// Get the supported management features for a driver
 pub fn get_supported_management_features(driver_name: &str) -> Option<&'static [&'static str]> {
     match driver_name {
-        "e1000" | "e1000e" | "rtl8169" | "virtio_net" => Some(&["hot_plugging", "firmware_update", "configuration_management", "remote_management"]),
+        // Only drivers serving orion_fwupdate control requests advertise firmware updates
+        "e1000e" => Some(&["hot_plugging", "firmware_update", "configuration_management", "remote_management"]),
+        "e1000" | "rtl8169" | "virtio_net" => Some(&["hot_plugging", "configuration_management", "remote_management"]),
         _ => None,
     }
 }
 
-/// Get comprehensive driver information
-pub fn get_comprehensive_driver_info(driver_name: &str) -> Option<String> {
-    let mut info = String::new();
-    
-    if let Some(desc) = get_driver_info(driver_name) {
-        info.push_str(&format!("Driver: {}\n", desc));
-    }
-    
-    if let Some(version) = get_driver_version(driver_name) {
-        info.push_str(&format!("Version: {}\n", version));
-    }
-    
-    if let Some(speed) = get_max_speed(driver_name) {
-        info.push_str(&format!("Max Speed: {} Mbps\n", speed));
-    }
-    
-    if let Some(mtu) = get_max_mtu(driver_name) {
-        info.push_str(&format!("Max MTU: {}\n", mtu));
-    }
-    
-    if let Some(ring_size) = get_default_ring_size(driver_name) {
-        info.push_str(&format!("Default Ring Size: {}\n", ring_size));
-    }
-    
-    if let Some(buffer_size) = get_default_buffer_size(driver_name) {
-        info.push_str(&format!("Default Buffer Size: {}\n", buffer_size));
+/// Features of one driver, as a set over available_features()
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeatureSet(u32);
+
+impl FeatureSet {
+    /// Features the driver supports
+    pub fn of(driver_name: &str) -> Self {
+        let bits = available_features()
+            .iter()
+            .enumerate()
+            .filter(|(_, feature)| driver_supports_feature(driver_name, feature))
+            .fold(0, |bits, (index, _)| bits | 1 << index);
+        FeatureSet(bits)
     }
-    
-    if let Some(speeds) = get_supported_link_speeds(driver_name) {
-        info.push_str(&format!("Supported Speeds: {:?} Mbps\n", speeds));
+
+    pub fn contains(&self, feature: &str) -> bool {
+        self.iter().any(|supported| supported == feature)
     }
-    
-    if let Some(duplex_modes) = get_supported_duplex_modes(driver_name) {
-        info.push_str(&format!("Supported Duplex Modes: {:?}\n", duplex_modes));
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
     }
-    
-    if let Some(features) = get_supported_vlan_features(driver_name) {
-        info.push_str(&format!("VLAN Features: {:?}\n", features));
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
     }
-    
-    if let Some(features) = get_supported_qos_features(driver_name) {
-        info.push_str(&format!("QoS Features: {:?}\n", features));
+
+    /// Feature names, in the order of available_features()
+    pub fn iter(&self) -> impl Iterator<Item = &'static str> {
+        let bits = self.0;
+        available_features()
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| bits & (1 << index) != 0)
+            .map(|(_, feature)| *feature)
     }
-    
-    if let Some(features) = get_supported_security_features(driver_name) {
-        info.push_str(&format!("Security Features: {:?}\n", features));
+}
+
+/// Everything known about one network driver. Built from static tables,
+/// so reporting on a driver never allocates; Display renders the text
+/// form and DriversJson the management tools' one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DriverReport {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub version: &'static str,
+    pub max_speed_mbps: u32,
+    pub max_mtu: u16,
+    pub default_ring_size: usize,
+    pub default_buffer_size: usize,
+    pub link_speeds_mbps: &'static [u32],
+    pub duplex_modes: &'static [&'static str],
+    pub vlan_features: &'static [&'static str],
+    pub qos_features: &'static [&'static str],
+    pub security_features: &'static [&'static str],
+    pub features: FeatureSet,
+}
+
+impl DriverReport {
+    /// Report on a driver, None if it is not one of available_drivers()
+    pub fn for_driver(driver_name: &str) -> Option<Self> {
+        let name = *available_drivers().iter().find(|&&name| name == driver_name)?;
+        Some(DriverReport {
+            name,
+            description: get_driver_info(name).unwrap_or(""),
+            version: get_driver_version(name).unwrap_or(VERSION),
+            max_speed_mbps: get_max_speed(name).unwrap_or(0),
+            max_mtu: get_max_mtu(name).unwrap_or(1500),
+            default_ring_size: get_default_ring_size(name).unwrap_or(0),
+            default_buffer_size: get_default_buffer_size(name).unwrap_or(0),
+            link_speeds_mbps: get_supported_link_speeds(name).unwrap_or(&[]),
+            duplex_modes: get_supported_duplex_modes(name).unwrap_or(&[]),
+            vlan_features: get_supported_vlan_features(name).unwrap_or(&[]),
+            qos_features: get_supported_qos_features(name).unwrap_or(&[]),
+            security_features: get_supported_security_features(name).unwrap_or(&[]),
+            features: FeatureSet::of(name),
+        })
     }
-    
-    if Some(info.is_empty()) == Some(false) {
-        Some(info)
-    } else {
-        None
+}
+
+impl fmt::Display for DriverReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Driver: {}", self.description)?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Max Speed: {} Mbps", self.max_speed_mbps)?;
+        writeln!(f, "Max MTU: {}", self.max_mtu)?;
+        writeln!(f, "Default Ring Size: {}", self.default_ring_size)?;
+        writeln!(f, "Default Buffer Size: {}", self.default_buffer_size)?;
+        writeln!(f, "Supported Speeds: {:?} Mbps", self.link_speeds_mbps)?;
+        writeln!(f, "Supported Duplex Modes: {:?}", self.duplex_modes)?;
+        writeln!(f, "VLAN Features: {:?}", self.vlan_features)?;
+        writeln!(f, "QoS Features: {:?}", self.qos_features)?;
+        writeln!(f, "Security Features: {:?}", self.security_features)
     }
 }
 
-/// Get a summary of all available drivers
-pub fn get_drivers_summary() -> String {
-    let mut summary = String::new();
-    summary.push_str("=== Orion OS Network Drivers Summary ===\n\n");
-    
-    for driver in available_drivers() {
-        if let Some(info) = get_driver_info(driver) {
-            summary.push_str(&format!("{}: {}\n", driver, info));
+/// Report on every available driver
+pub fn driver_reports() -> impl Iterator<Item = DriverReport> {
+    available_drivers().iter().filter_map(|name| DriverReport::for_driver(name))
+}
+
+/// Summary of the driver suite; Display renders the text form
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DriversSummary;
+
+impl fmt::Display for DriversSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== Orion OS Network Drivers Summary ===")?;
+        writeln!(f)?;
+        for report in driver_reports() {
+            writeln!(f, "{}: {}", report.name, report.description)?;
+            writeln!(f, "  Version: {}", report.version)?;
+            writeln!(f, "  Max Speed: {} Mbps", report.max_speed_mbps)?;
+            writeln!(f)?;
         }
-        if let Some(version) = get_driver_version(driver) {
-            summary.push_str(&format!("  Version: {}\n", version));
+        writeln!(f, "Total Drivers: {}", available_drivers().len())?;
+        writeln!(f, "Library Version: {}", VERSION)?;
+        writeln!(f, "Author: {}", AUTHOR)
+    }
+}
+
+fn json_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
         }
-        if let Some(speed) = get_max_speed(driver) {
-            summary.push_str(&format!("  Max Speed: {} Mbps\n", speed));
+    }
+    f.write_char('"')
+}
+
+/// Driver inventory as a JSON document, the machine readable counterpart
+/// of DriversSummary served by the management API, written straight to
+/// the formatter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DriversJson;
+
+impl fmt::Display for DriversJson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{\"version\":")?;
+        json_string(f, VERSION)?;
+        f.write_str(",\"drivers\":[")?;
+        for (index, report) in driver_reports().enumerate() {
+            if index > 0 {
+                f.write_char(',')?;
+            }
+            f.write_str("{\"name\":")?;
+            json_string(f, report.name)?;
+            f.write_str(",\"description\":")?;
+            json_string(f, report.description)?;
+            f.write_str(",\"version\":")?;
+            json_string(f, report.version)?;
+            write!(f, ",\"max_speed_mbps\":{},\"max_mtu\":{},\"features\":[", report.max_speed_mbps, report.max_mtu)?;
+            for (feature_index, feature) in report.features.iter().enumerate() {
+                if feature_index > 0 {
+                    f.write_char(',')?;
+                }
+                json_string(f, feature)?;
+            }
+            f.write_str("]}")?;
         }
-        summary.push_str("\n");
+        f.write_str("]}")
     }
-    
-    summary.push_str(&format!("Total Drivers: {}\n", available_drivers().len()));
-    summary.push_str(&format!("Library Version: {}\n", VERSION));
-    summary.push_str(&format!("Author: {}\n", AUTHOR));
-    
-    summary
 }
 
 #[cfg(test)]
@@ -402,10 +529,58 @@ mod tests {
         assert_eq!(get_max_mtu("nonexistent"), None);
     }
     
+    #[test]
+    fn test_driver_reports() {
+        let reports: Vec<DriverReport> = driver_reports().collect();
+        assert_eq!(reports.len(), available_drivers().len());
+        assert!(reports[0].features.contains("jumbo_frames"));
+        assert!(!reports[3].features.contains("wake_on_lan"));
+        assert_eq!(reports[0].features.iter().count(), reports[0].features.len());
+        assert!(DriverReport::for_driver("nonexistent").is_none());
+
+        let text = DriverReport::for_driver("virtio_net").unwrap().to_string();
+        assert!(text.starts_with("Driver: VirtIO Network Driver\nVersion: 2.0.0\n"));
+        assert!(text.contains("Supported Speeds: [1000, 10000] Mbps\n"));
+        let summary = DriversSummary.to_string();
+        assert!(summary.contains("rtl8169: Realtek RTL8169 Gigabit Ethernet Driver\n  Version: 2.0.0\n"));
+        assert!(summary.ends_with("Author: Jeremy Noverraz <jeremy@orion-os.dev>\n"));
+    }
+
+    #[test]
+    fn test_drivers_json() {
+        let json = DriversJson.to_string();
+        assert!(json.starts_with("{\"version\":\"2.0.0\",\"drivers\":[{\"name\":\"e1000\","));
+        assert!(json.contains("\"max_speed_mbps\":10000"));
+        assert!(json.ends_with("]}]}"));
+    }
+
     #[test]
     fn test_feature_support() {
         assert!(driver_supports_feature("e1000", "hardware_checksum_offload"));
         assert!(driver_supports_feature("e1000", "jumbo_frames"));
         assert!(!driver_supports_feature("e1000", "nonexistent_feature"));
     }
+
+    #[test]
+    fn test_aggregated_statistics_deltas() {
+        let mut aggregated = AggregatedNetworkStats::default();
+        let (mut eth0, mut eth1) = (NetworkStats::default(), NetworkStats::default());
+        aggregated.refresh([("eth0", &eth0), ("eth1", &eth1)].into_iter(), 1_000_000_000);
+        assert!(aggregated.delta().is_none());
+
+        eth0.record_rx(0, 1000);
+        eth1.record_rx(1, 500);
+        eth1.record_rx_error(ErrorKind::Crc);
+        aggregated.refresh([("eth0", &eth0), ("eth1", &eth1)].into_iter(), 1_500_000_000);
+        // Totals are the latest counters, not a running sum of snapshots
+        aggregated.refresh([("eth0", &eth0), ("eth1", &eth1)].into_iter(), 2_000_000_000);
+        assert_eq!((aggregated.totals.rx_packets, aggregated.totals.rx_bytes), (2, 1500));
+        assert_eq!(aggregated.delta().unwrap().rates().rx_bytes_per_sec, 0);
+
+        eth1.record_rx(1, 250);
+        aggregated.refresh([("eth0", &eth0), ("eth1", &eth1)].into_iter(), 2_500_000_000);
+        let eth1_delta = aggregated.interface_delta("eth1").unwrap();
+        assert_eq!((eth1_delta.stats.rx_queues[1].bytes, eth1_delta.rates().rx_bytes_per_sec), (250, 500));
+        assert_eq!(aggregated.totals.rx_error_kinds.get(ErrorKind::Crc), 1);
+    }
 }
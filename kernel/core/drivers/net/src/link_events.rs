@@ -0,0 +1,335 @@
+/*
+ * Orion Operating System - Link State Events
+ *
+ * Link changes pushed to subscribers instead of being polled. Every
+ * interface has a monitor fed with what its driver reports; a new state is
+ * only announced once it has held for the debounce interval, and a link
+ * that drops and comes back within it is announced as a re-plug, the
+ * cable possibly now leading to another network.
+ *
+ * Subscriptions go through the LINK_IOCTL_CONTROL ioctl of the network
+ * manager. All fields are little-endian; requests start with a 32-bit
+ * opcode and replies are a 32-bit signed status (0 or a negative errno):
+ *
+ *   SUBSCRIBE    port:u64 mask:u32 name_len:u32 name  -> (empty)
+ *   UNSUBSCRIBE  port:u64                             -> (empty)
+ *
+ * `mask` selects event kinds (bit 1 << kind); an empty name subscribes to
+ * every interface. Subscribers receive on `port`
+ *
+ *   LINK  kind:u32 speed_mbps:u32 duplex:u32 name_len:u32 name
+ *
+ * with kind 1 up, 2 down, 3 speed or duplex change, 4 re-plug.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Ioctl of the network manager carrying subscription requests
+pub const LINK_IOCTL_CONTROL: u32 = 0x2020;
+
+// Control opcodes
+pub const LINK_OP_SUBSCRIBE: u32 = 1;
+pub const LINK_OP_UNSUBSCRIBE: u32 = 2;
+
+// Event sent to subscribers, and its kinds
+pub const EVENT_LINK: u32 = 0x8101;
+pub const LINK_EVENT_UP: u32 = 1;
+pub const LINK_EVENT_DOWN: u32 = 2;
+pub const LINK_EVENT_CHANGED: u32 = 3;
+pub const LINK_EVENT_REPLUGGED: u32 = 4;
+pub const LINK_EVENTS_ALL: u32 = 0x1E;
+
+// Control reply status codes
+pub const LINK_OK: i32 = 0;
+pub const LINK_EINVAL: i32 = -22;
+pub const LINK_ENOSPC: i32 = -28;
+
+/// How long a new link state must hold before it is announced
+pub const LINK_DEBOUNCE_NS: u64 = 200_000_000;
+pub const MAX_LINK_SUBSCRIBERS: usize = 16;
+const MAX_INTERFACE_NAME: usize = 16;
+
+/// Speed and duplex of a link that is up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkMode {
+    pub speed_mbps: u32,
+    pub duplex: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkEvent {
+    Up(LinkMode),
+    Down,
+    /// Still up, at another speed or duplex
+    Changed(LinkMode),
+    /// Went down and came back up within the debounce interval
+    Replugged(LinkMode),
+}
+
+impl LinkEvent {
+    pub fn kind(&self) -> u32 {
+        match self {
+            LinkEvent::Up(_) => LINK_EVENT_UP,
+            LinkEvent::Down => LINK_EVENT_DOWN,
+            LinkEvent::Changed(_) => LINK_EVENT_CHANGED,
+            LinkEvent::Replugged(_) => LINK_EVENT_REPLUGGED,
+        }
+    }
+
+    /// Link mode after the event, None when down
+    pub fn mode(&self) -> Option<LinkMode> {
+        match *self {
+            LinkEvent::Up(mode) | LinkEvent::Changed(mode) | LinkEvent::Replugged(mode) => Some(mode),
+            LinkEvent::Down => None,
+        }
+    }
+
+    /// LINK message for `interface`
+    pub fn encode(&self, interface: &str) -> Vec<u8> {
+        let mode = self.mode().unwrap_or(LinkMode { speed_mbps: 0, duplex: false });
+        let mut out = Vec::with_capacity(20 + interface.len());
+        for value in [EVENT_LINK, self.kind(), mode.speed_mbps, mode.duplex as u32, interface.len() as u32] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(interface.as_bytes());
+        out
+    }
+}
+
+/// Debounced link state of one interface
+#[derive(Debug, Clone)]
+pub struct LinkMonitor {
+    debounce_ns: u64,
+    reported: Option<LinkMode>,
+    /// State observed differing from the reported one, since when
+    pending: Option<(Option<LinkMode>, u64)>,
+    /// The link was seen down since the last announcement
+    bounced: bool,
+}
+
+impl LinkMonitor {
+    /// Monitor of a link taken to be down
+    pub fn new(debounce_ns: u64) -> Self {
+        Self { debounce_ns, reported: None, pending: None, bounced: false }
+    }
+
+    pub fn reported(&self) -> Option<LinkMode> {
+        self.reported
+    }
+
+    /// Feed the state the driver reports at `now_ns`; call it periodically
+    /// even without change so that pending states get announced
+    pub fn observe(&mut self, observed: Option<LinkMode>, now_ns: u64) -> Option<LinkEvent> {
+        if observed.is_none() && self.reported.is_some() {
+            self.bounced = true;
+        }
+        if observed == self.reported && !self.bounced {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match self.pending {
+            Some((state, since)) if state == observed => since,
+            _ => {
+                self.pending = Some((observed, now_ns));
+                now_ns
+            }
+        };
+        if now_ns.saturating_sub(since) < self.debounce_ns {
+            return None;
+        }
+
+        let event = match (self.reported, observed) {
+            (_, None) => LinkEvent::Down,
+            (Some(_), Some(mode)) if self.bounced => LinkEvent::Replugged(mode),
+            (Some(_), Some(mode)) => LinkEvent::Changed(mode),
+            (None, Some(mode)) => LinkEvent::Up(mode),
+        };
+        self.reported = observed;
+        self.pending = None;
+        self.bounced = false;
+        Some(event)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Subscription {
+    mask: u32,
+    /// None for every interface
+    interface: Option<String>,
+}
+
+/// Ports link events are sent to
+#[derive(Debug, Clone, Default)]
+pub struct LinkSubscribers {
+    subscriptions: BTreeMap<u64, Subscription>,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+impl LinkSubscribers {
+    /// Subscribe `port`, or change its subscription
+    pub fn subscribe(&mut self, port: u64, mask: u32, interface: Option<&str>) -> Result<(), i32> {
+        if mask & LINK_EVENTS_ALL == 0 || interface.is_some_and(|name| name.is_empty() || name.len() > MAX_INTERFACE_NAME) {
+            return Err(LINK_EINVAL);
+        }
+        if self.subscriptions.len() >= MAX_LINK_SUBSCRIBERS && !self.subscriptions.contains_key(&port) {
+            return Err(LINK_ENOSPC);
+        }
+        self.subscriptions.insert(port, Subscription { mask, interface: interface.map(String::from) });
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, port: u64) -> bool {
+        self.subscriptions.remove(&port).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Ports interested in `event` on `interface`
+    pub fn recipients<'a>(&'a self, interface: &'a str, event: &LinkEvent) -> impl Iterator<Item = u64> + 'a {
+        let bit = 1 << event.kind();
+        self.subscriptions
+            .iter()
+            .filter(move |(_, subscription)| {
+                subscription.mask & bit != 0 && subscription.interface.as_deref().is_none_or(|name| name == interface)
+            })
+            .map(|(&port, _)| port)
+    }
+
+    /// Answer a LINK_IOCTL_CONTROL request
+    pub fn handle_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let status = match self.control(request) {
+            Ok(()) => LINK_OK,
+            Err(status) => status,
+        };
+        status.to_le_bytes().to_vec()
+    }
+
+    fn control(&mut self, request: &[u8]) -> Result<(), i32> {
+        let port = read_u64(request, 4).ok_or(LINK_EINVAL)?;
+        match read_u32(request, 0) {
+            Some(LINK_OP_SUBSCRIBE) => {
+                let mask = read_u32(request, 12).ok_or(LINK_EINVAL)?;
+                let length = read_u32(request, 16).ok_or(LINK_EINVAL)? as usize;
+                let name = request.get(20..).filter(|name| name.len() == length).ok_or(LINK_EINVAL)?;
+                let name = core::str::from_utf8(name).map_err(|_| LINK_EINVAL)?;
+                self.subscribe(port, mask, (!name.is_empty()).then_some(name))
+            }
+            Some(LINK_OP_UNSUBSCRIBE) if request.len() == 12 => {
+                self.unsubscribe(port);
+                Ok(())
+            }
+            _ => Err(LINK_EINVAL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const GIGABIT: LinkMode = LinkMode { speed_mbps: 1000, duplex: true };
+    const FAST: LinkMode = LinkMode { speed_mbps: 100, duplex: true };
+
+    #[test]
+    fn test_debounced_transitions() {
+        let mut monitor = LinkMonitor::new(100);
+        assert_eq!(monitor.observe(Some(GIGABIT), 0), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 99), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 100), Some(LinkEvent::Up(GIGABIT)));
+        assert_eq!(monitor.observe(Some(GIGABIT), 500), None);
+
+        assert_eq!(monitor.observe(Some(FAST), 600), None);
+        assert_eq!(monitor.observe(Some(FAST), 700), Some(LinkEvent::Changed(FAST)));
+
+        assert_eq!(monitor.observe(None, 800), None);
+        assert_eq!(monitor.observe(None, 950), Some(LinkEvent::Down));
+        assert_eq!(monitor.reported(), None);
+    }
+
+    #[test]
+    fn test_replug_and_glitch() {
+        let mut monitor = LinkMonitor::new(100);
+        monitor.observe(Some(GIGABIT), 0);
+        monitor.observe(Some(GIGABIT), 100);
+
+        // Down and back within the interval: one re-plug, no down/up pair
+        assert_eq!(monitor.observe(None, 200), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 250), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 349), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 350), Some(LinkEvent::Replugged(GIGABIT)));
+
+        // A speed blip shorter than the interval goes unnoticed
+        assert_eq!(monitor.observe(Some(FAST), 400), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 450), None);
+        assert_eq!(monitor.observe(Some(GIGABIT), 600), None);
+
+        let mut immediate = LinkMonitor::new(0);
+        assert_eq!(immediate.observe(Some(FAST), 5), Some(LinkEvent::Up(FAST)));
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let mut subscribers = LinkSubscribers::default();
+        let mut request = Vec::new();
+        for value in [LINK_OP_SUBSCRIBE, 7, 0, 1 << LINK_EVENT_DOWN, 4] {
+            request.extend_from_slice(&value.to_le_bytes());
+        }
+        request.extend_from_slice(b"eth0");
+        assert_eq!(subscribers.handle_control(&request), LINK_OK.to_le_bytes());
+        assert_eq!(subscribers.handle_control(&request[..22]), LINK_EINVAL.to_le_bytes());
+        subscribers.subscribe(9, LINK_EVENTS_ALL, None).unwrap();
+
+        let ports: Vec<u64> = subscribers.recipients("eth0", &LinkEvent::Down).collect();
+        assert_eq!(ports, vec![7, 9]);
+        let ports: Vec<u64> = subscribers.recipients("eth1", &LinkEvent::Down).collect();
+        assert_eq!(ports, vec![9]);
+        assert_eq!(subscribers.recipients("eth0", &LinkEvent::Up(GIGABIT)).count(), 1);
+
+        let mut request = LINK_OP_UNSUBSCRIBE.to_le_bytes().to_vec();
+        request.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(subscribers.handle_control(&request), LINK_OK.to_le_bytes());
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(subscribers.subscribe(9, 0, None), Err(LINK_EINVAL));
+
+        for port in 10..25 {
+            subscribers.subscribe(port, LINK_EVENTS_ALL, None).unwrap();
+        }
+        assert_eq!(subscribers.subscribe(99, LINK_EVENTS_ALL, None), Err(LINK_ENOSPC));
+    }
+
+    #[test]
+    fn test_event_encoding() {
+        let message = LinkEvent::Replugged(FAST).encode("eth3");
+        assert_eq!(message.len(), 24);
+        assert_eq!(read_u32(&message, 0), Some(EVENT_LINK));
+        assert_eq!(read_u32(&message, 4), Some(LINK_EVENT_REPLUGGED));
+        assert_eq!(read_u32(&message, 8), Some(100));
+        assert_eq!(read_u32(&message, 12), Some(1));
+        assert_eq!(&message[20..], b"eth3");
+        assert_eq!(read_u32(&LinkEvent::Down.encode("eth3"), 8), Some(0));
+    }
+}
@@ -26,10 +26,16 @@ extern crate alloc;
 use orion_driver::{
     NetworkDriver, DeviceInfo, DriverError, DriverResult, OrionDriver,
     MessageLoop, ReceivedMessage, IpcInterface, MmioAccessor, MmioPermissions,
-    LinkStatus, NetworkStats, BusType,
+    LinkStatus, BusType,
 };
+use orion_ipc::IpcChannel;
+use orion_netstats::{NetworkStats, StatsDelta, StatsSampler};
+use orion_sys::clock_get;
 use alloc::{vec::Vec, collections::BTreeMap, boxed::Box, string::String};
-use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::get_default_ring_size;
+use super::link_events::{LinkEvent, LinkMode, LinkMonitor, LinkSubscribers, LINK_DEBOUNCE_NS, LINK_IOCTL_CONTROL};
+use super::ring_config::{reconfigure, Offloads, Reconfigurable, RingConfig};
 
 // Import all network drivers
 use super::e1000::AdvancedE1000Driver;
@@ -49,30 +55,70 @@ pub struct NetworkInterface {
     pub driver_name: String,
     pub driver_version: String,
     pub statistics: NetworkStats,
+    /// Descriptor rings and offloads the driver runs with
+    pub rings: RingConfig,
+    pub offloads: Offloads,
 }
 
 /// Network driver manager
 pub struct NetworkDriverManager {
     interfaces: Vec<NetworkInterface>,
-    drivers: BTreeMap<String, Box<dyn NetworkDriver>>,
+    drivers: BTreeMap<String, Box<dyn Reconfigurable>>,
     active_interfaces: Vec<String>,
     statistics: AggregatedNetworkStats,
     configuration: NetworkConfiguration,
+    /// Debounced link state per interface name
+    link_monitors: BTreeMap<String, LinkMonitor>,
+    link_subscribers: LinkSubscribers,
+    /// Channel link events leave by, opened with the first subscriber
+    link_channel: Option<IpcChannel>,
+}
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+/// Rings an interface starts with, until its driver reports its own
+fn default_rings(driver_name: &str) -> RingConfig {
+    let size = get_default_ring_size(driver_name).unwrap_or(256) as u16;
+    RingConfig { rx: size, tx: size }
+}
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
 }
 
 /// Aggregated network statistics across all interfaces
 #[derive(Debug, Clone, Default)]
 pub struct AggregatedNetworkStats {
-    pub total_rx_packets: AtomicU64,
-    pub total_tx_packets: AtomicU64,
-    pub total_rx_bytes: AtomicU64,
-    pub total_tx_bytes: AtomicU64,
-    pub total_rx_errors: AtomicU64,
-    pub total_tx_errors: AtomicU64,
-    pub total_rx_dropped: AtomicU64,
-    pub total_tx_dropped: AtomicU64,
-    pub total_interfaces: AtomicU64,
-    pub total_active_interfaces: AtomicU64,
+    /// Sum of the latest counters of every interface
+    pub totals: NetworkStats,
+    pub total_interfaces: u64,
+    pub total_active_interfaces: u64,
+    /// Intervals between statistics refreshes, system-wide and per interface
+    sampler: StatsSampler,
+    interface_samplers: BTreeMap<String, StatsSampler>,
+}
+
+impl AggregatedNetworkStats {
+    /// Record the latest counters of every interface, taken at `now_ns`
+    pub fn refresh<'a>(&mut self, interfaces: impl Iterator<Item = (&'a str, &'a NetworkStats)>, now_ns: u64) {
+        let mut totals = NetworkStats::default();
+        for (name, stats) in interfaces {
+            totals.accumulate(stats);
+            self.interface_samplers.entry(String::from(name)).or_default().sample(stats, now_ns);
+        }
+        self.totals = totals;
+        self.sampler.sample(&totals, now_ns);
+    }
+
+    /// System-wide counts over the last refresh interval, for rates
+    pub fn delta(&self) -> Option<&StatsDelta> {
+        self.sampler.last()
+    }
+
+    /// Counts of one interface over the last refresh interval
+    pub fn interface_delta(&self, name: &str) -> Option<&StatsDelta> {
+        self.interface_samplers.get(name).and_then(StatsSampler::last)
+    }
 }
 
 /// Network configuration
@@ -116,6 +162,9 @@ impl NetworkDriverManager {
             active_interfaces: Vec::new(),
             statistics: AggregatedNetworkStats::default(),
             configuration: NetworkConfiguration::default(),
+            link_monitors: BTreeMap::new(),
+            link_subscribers: LinkSubscribers::default(),
+            link_channel: None,
         }
     }
     
@@ -188,6 +237,8 @@ impl NetworkDriverManager {
             driver_name: "e1000".to_string(),
             driver_version: "2.0.0".to_string(),
             statistics: NetworkStats::default(),
+            rings: default_rings("e1000"),
+            offloads: Offloads::NONE,
         };
         
         let e1000e_interface = NetworkInterface {
@@ -200,6 +251,8 @@ impl NetworkDriverManager {
             driver_name: "e1000e".to_string(),
             driver_version: "2.0.0".to_string(),
             statistics: NetworkStats::default(),
+            rings: default_rings("e1000e"),
+            offloads: Offloads::NONE,
         };
         
         let rtl8169_interface = NetworkInterface {
@@ -212,6 +265,8 @@ impl NetworkDriverManager {
             driver_name: "rtl8169".to_string(),
             driver_version: "2.0.0".to_string(),
             statistics: NetworkStats::default(),
+            rings: default_rings("rtl8169"),
+            offloads: Offloads::NONE,
         };
         
         let virtio_interface = NetworkInterface {
@@ -224,6 +279,8 @@ impl NetworkDriverManager {
             driver_name: "virtio_net".to_string(),
             driver_version: "2.0.0".to_string(),
             statistics: NetworkStats::default(),
+            rings: default_rings("virtio_net"),
+            offloads: Offloads::NONE,
         };
         
         self.interfaces.push(e1000_interface);
@@ -250,6 +307,7 @@ impl NetworkDriverManager {
                 if let Ok(mac) = driver.get_mac_address() {
                     interface.mac_address = mac;
                 }
+                (interface.rings, interface.offloads) = driver.ring_config();
                 
                 // Mark interface as initialized
                 interface.link_up = true;
@@ -258,8 +316,8 @@ impl NetworkDriverManager {
         }
         
         // Update statistics
-        self.statistics.total_interfaces.store(self.interfaces.len() as u64, Ordering::Relaxed);
-        self.statistics.total_active_interfaces.store(self.active_interfaces.len() as u64, Ordering::Relaxed);
+        self.statistics.total_interfaces = self.interfaces.len() as u64;
+        self.statistics.total_active_interfaces = self.active_interfaces.len() as u64;
         
         Ok(())
     }
@@ -282,11 +340,13 @@ impl NetworkDriverManager {
     
     /// Start network monitoring
     fn start_network_monitoring(&mut self) -> DriverResult<()> {
-        // This would typically involve:
-        // 1. Setting up periodic link status checks
-        // 2. Monitoring interface statistics
-        // 3. Handling link state changes
-        // 4. Managing interface up/down states
+        // Link state starts from what the drivers report now; changes are
+        // then picked up by poll_link_state
+        self.link_monitors.clear();
+        for interface in &self.interfaces {
+            self.link_monitors.insert(interface.name.clone(), LinkMonitor::new(LINK_DEBOUNCE_NS));
+        }
+        self.poll_link_state(monotonic_ns());
         
         Ok(())
     }
@@ -359,6 +419,25 @@ impl NetworkDriverManager {
         Ok(())
     }
     
+    /// Change the descriptor rings and offloads of a running interface.
+    /// The request is checked against the limits the driver found when
+    /// probing the device; the driver then quiesces, reallocates its rings
+    /// and reprograms the device without touching the link, which link
+    /// event subscribers therefore never see go down
+    pub fn configure(&mut self, interface_name: &str, rings: RingConfig, offloads: Offloads) -> DriverResult<()> {
+        let interface = self
+            .interfaces
+            .iter_mut()
+            .find(|iface| iface.name == interface_name)
+            .ok_or(DriverError::DeviceNotFound)?;
+        let driver = self.drivers.get_mut(&interface.driver_name).ok_or(DriverError::DeviceNotFound)?;
+        let result = reconfigure(driver.as_mut(), rings, offloads);
+        // A failed change leaves the previous rings, or none running if
+        // even those could not be restored
+        (interface.rings, interface.offloads) = driver.ring_config();
+        result
+    }
+    
     /// Reconfigure drivers based on new configuration
     fn reconfigure_drivers(&mut self) -> DriverResult<()> {
         // This would typically involve:
@@ -395,7 +474,7 @@ impl NetworkDriverManager {
             if !self.active_interfaces.contains(&interface_name.to_string()) {
                 self.active_interfaces.push(interface_name.to_string());
             }
-            self.statistics.total_active_interfaces.fetch_add(1, Ordering::Relaxed);
+            self.statistics.total_active_interfaces += 1;
             Ok(())
         } else {
             Err(DriverError::DeviceNotFound)
@@ -407,38 +486,92 @@ impl NetworkDriverManager {
         if let Some(interface) = self.interfaces.iter_mut().find(|iface| iface.name == interface_name) {
             interface.link_up = false;
             self.active_interfaces.retain(|name| name != interface_name);
-            self.statistics.total_active_interfaces.fetch_sub(1, Ordering::Relaxed);
+            self.statistics.total_active_interfaces = self.statistics.total_active_interfaces.saturating_sub(1);
             Ok(())
         } else {
             Err(DriverError::DeviceNotFound)
         }
     }
     
+    /// Read the link status of every interface, update the interfaces and
+    /// return the changes that held for the debounce interval. Called from
+    /// interrupts and periodically, for pending changes to come through
+    pub fn poll_link_state(&mut self, now_ns: u64) -> Vec<(String, LinkEvent)> {
+        let mut events = Vec::new();
+        for interface in &self.interfaces {
+            let Some(driver) = self.drivers.get(&interface.driver_name) else { continue };
+            let observed = match driver.link_status() {
+                LinkStatus::Up { speed_mbps, duplex } => Some(LinkMode { speed_mbps: speed_mbps as u32, duplex }),
+                _ => None,
+            };
+            let monitor = self
+                .link_monitors
+                .entry(interface.name.clone())
+                .or_insert_with(|| LinkMonitor::new(LINK_DEBOUNCE_NS));
+            if let Some(event) = monitor.observe(observed, now_ns) {
+                events.push((interface.name.clone(), event));
+            }
+        }
+        for (name, event) in &events {
+            self.apply_link_event(name, event);
+        }
+        events
+    }
+    
+    fn apply_link_event(&mut self, name: &str, event: &LinkEvent) {
+        let Some(interface) = self.interfaces.iter_mut().find(|iface| iface.name == name) else { return };
+        interface.link_up = event.mode().is_some();
+        if let Some(mode) = event.mode() {
+            interface.link_speed = mode.speed_mbps;
+            interface.duplex_mode = mode.duplex;
+        }
+        let active = self.active_interfaces.iter().any(|active| active == name);
+        if interface.link_up && !active {
+            self.active_interfaces.push(String::from(name));
+        } else if !interface.link_up && active {
+            self.active_interfaces.retain(|active| active != name);
+        }
+        self.statistics.total_active_interfaces = self.active_interfaces.len() as u64;
+    }
+    
+    /// Poll link state and send the changes to their subscribers
+    pub fn publish_link_events(&mut self, now_ns: u64) {
+        let events = self.poll_link_state(now_ns);
+        if events.is_empty() || self.link_subscribers.is_empty() {
+            return;
+        }
+        let channel = self.link_channel.get_or_insert_with(IpcChannel::new);
+        for (name, event) in &events {
+            let message = event.encode(name);
+            for port in self.link_subscribers.recipients(name, event) {
+                channel.send(port, &message);
+            }
+        }
+    }
+    
+    /// Subscribers to link events, for the routing layer and DHCP client
+    pub fn link_subscribers(&mut self) -> &mut LinkSubscribers {
+        &mut self.link_subscribers
+    }
+    
     /// Get driver information
     pub fn get_driver_info(&self, driver_name: &str) -> Option<&dyn NetworkDriver> {
-        self.drivers.get(driver_name).map(|d| d.as_ref())
+        self.drivers.get(driver_name).map(|d| d.as_ref() as &dyn NetworkDriver)
     }
     
     /// Update interface statistics
     pub fn update_interface_statistics(&mut self) -> DriverResult<()> {
         for interface in &mut self.interfaces {
             if let Some(driver) = self.drivers.get(&interface.driver_name) {
-                // Get statistics from driver
-                let stats = driver.statistics();
-                interface.statistics = stats;
-                
-                // Update aggregated statistics
-                self.statistics.total_rx_packets.fetch_add(stats.rx_packets, Ordering::Relaxed);
-                self.statistics.total_tx_packets.fetch_add(stats.tx_packets, Ordering::Relaxed);
-                self.statistics.total_rx_bytes.fetch_add(stats.rx_bytes, Ordering::Relaxed);
-                self.statistics.total_tx_bytes.fetch_add(stats.tx_bytes, Ordering::Relaxed);
-                self.statistics.total_rx_errors.fetch_add(stats.rx_errors, Ordering::Relaxed);
-                self.statistics.total_tx_errors.fetch_add(stats.tx_errors, Ordering::Relaxed);
-                self.statistics.total_rx_dropped.fetch_add(stats.rx_dropped, Ordering::Relaxed);
-                self.statistics.total_tx_dropped.fetch_add(stats.tx_dropped, Ordering::Relaxed);
+                // Drivers report lifetime counters; the aggregate keeps the
+                // previous snapshot to derive per-interval deltas from them
+                interface.statistics = driver.statistics();
             }
         }
         
+        let interfaces = self.interfaces.iter().map(|iface| (iface.name.as_str(), &iface.statistics));
+        self.statistics.refresh(interfaces, monotonic_ns());
+        
         Ok(())
     }
     
@@ -449,12 +582,17 @@ impl NetworkDriverManager {
         diagnostics.push_str("=== Network Diagnostics Report ===\n");
         diagnostics.push_str(&format!("Total Interfaces: {}\n", self.interfaces.len()));
         diagnostics.push_str(&format!("Active Interfaces: {}\n", self.active_interfaces.len()));
-        diagnostics.push_str(&format!("Total RX Packets: {}\n", self.statistics.total_rx_packets.load(Ordering::Relaxed)));
-        diagnostics.push_str(&format!("Total TX Packets: {}\n", self.statistics.total_tx_packets.load(Ordering::Relaxed)));
-        diagnostics.push_str(&format!("Total RX Bytes: {}\n", self.statistics.total_rx_bytes.load(Ordering::Relaxed)));
-        diagnostics.push_str(&format!("Total TX Bytes: {}\n", self.statistics.total_tx_bytes.load(Ordering::Relaxed)));
-        diagnostics.push_str(&format!("Total RX Errors: {}\n", self.statistics.total_rx_errors.load(Ordering::Relaxed)));
-        diagnostics.push_str(&format!("Total TX Errors: {}\n", self.statistics.total_tx_errors.load(Ordering::Relaxed)));
+        diagnostics.push_str(&format!("Total RX Packets: {}\n", self.statistics.totals.rx_packets));
+        diagnostics.push_str(&format!("Total TX Packets: {}\n", self.statistics.totals.tx_packets));
+        diagnostics.push_str(&format!("Total RX Bytes: {}\n", self.statistics.totals.rx_bytes));
+        diagnostics.push_str(&format!("Total TX Bytes: {}\n", self.statistics.totals.tx_bytes));
+        diagnostics.push_str(&format!("Total RX Errors: {}\n", self.statistics.totals.rx_errors));
+        diagnostics.push_str(&format!("Total TX Errors: {}\n", self.statistics.totals.tx_errors));
+        if let Some(delta) = self.statistics.delta() {
+            let rates = delta.rates();
+            diagnostics.push_str(&format!("RX Rate: {} pkt/s, {} B/s\n", rates.rx_packets_per_sec, rates.rx_bytes_per_sec));
+            diagnostics.push_str(&format!("TX Rate: {} pkt/s, {} B/s\n", rates.tx_packets_per_sec, rates.tx_bytes_per_sec));
+        }
         
         diagnostics.push_str("\n=== Interface Details ===\n");
         for interface in &self.interfaces {
@@ -466,6 +604,8 @@ impl NetworkDriverManager {
             diagnostics.push_str(&format!("  Speed: {} Mbps\n", interface.link_speed));
             diagnostics.push_str(&format!("  Duplex: {}\n", if interface.duplex_mode { "Full" } else { "Half" }));
             diagnostics.push_str(&format!("  MTU: {}\n", interface.mtu));
+            diagnostics.push_str(&format!("  Rings: RX {} TX {}, offloads {:#x}\n",
+                interface.rings.rx, interface.rings.tx, interface.offloads.bits()));
             diagnostics.push_str(&format!("  Driver: {} v{}\n", interface.driver_name, interface.driver_version));
             diagnostics.push_str(&format!("  RX Packets: {}\n", interface.statistics.rx_packets));
             diagnostics.push_str(&format!("  TX Packets: {}\n", interface.statistics.tx_packets));
@@ -505,11 +645,9 @@ impl OrionDriver for NetworkDriverManager {
     }
     
     fn handle_irq(&mut self) -> DriverResult<()> {
-        // Handle network-related interrupts
-        // This would typically involve:
-        // 1. Processing driver interrupts
-        // 2. Updating interface states
-        // 3. Handling network events
+        // Link status changes interrupt too: subscribers hear about them
+        // as soon as they have settled
+        self.publish_link_events(monotonic_ns());
         
         Ok(())
     }
@@ -530,7 +668,14 @@ impl OrionDriver for NetworkDriverManager {
     
     fn handle_message(&mut self, message: ReceivedMessage, ipc: &mut dyn IpcInterface) -> DriverResult<()> {
         // Handle network-related messages
-        match message.message_type {
+        match message {
+            // Link event subscriptions answer with a status
+            ReceivedMessage::IoRequest(io_msg)
+                if matches!(io_msg.request_type, orion_driver::IoRequestType::Ioctl) && io_msg.length == LINK_IOCTL_CONTROL =>
+            {
+                let reply = self.link_subscribers.handle_control(&io_msg.data);
+                ipc.send_response(io_msg.header.sequence, 0, &reply)
+            }
             // Network configuration messages
             // Interface management messages
             // Statistics request messages
@@ -30,10 +30,12 @@ extern crate alloc;
 use orion_driver::{
     NetworkDriver, DeviceInfo, DriverError, DriverResult, OrionDriver,
     MessageLoop, ReceivedMessage, IpcInterface, MmioAccessor, MmioPermissions,
-    LinkStatus, NetworkStats, BusType,
+    LinkStatus, NetworkStats, BusType, IoRequestType,
 };
 use alloc::{vec::Vec, collections::BTreeMap, boxed::Box, string::String};
-use core::sync::atomic::{AtomicU64, Ordering};
+use orion_phy::{handle_control as handle_phy_control, mii, Mdio, PhyError, PHY_IOCTL_CONTROL};
+
+use super::ring_config::{Offloads, Reconfigurable, RingConfig, RingLimits};
 
 // ========================================
 // RTL8169 CONSTANTS AND ENUMS
@@ -249,30 +251,31 @@ pub enum RTL8169DuplexMode {
 }
 
 // RTL8169 network statistics
-#[derive(Debug, Clone, Default)]
-pub struct RTL8169NetworkStats {
-    pub rx_packets: AtomicU64,
-    pub tx_packets: AtomicU64,
-    pub rx_bytes: AtomicU64,
-    pub tx_bytes: AtomicU64,
-    pub rx_errors: AtomicU64,
-    pub tx_errors: AtomicU64,
-    pub rx_dropped: AtomicU64,
-    pub tx_dropped: AtomicU64,
-    pub rx_fifo_errors: AtomicU64,
-    pub tx_fifo_errors: AtomicU64,
-    pub rx_frame_errors: AtomicU64,
-    pub tx_carrier_errors: AtomicU64,
-    pub rx_collision_errors: AtomicU64,
-    pub tx_collision_errors: AtomicU64,
-    pub rx_crc_errors: AtomicU64,
-    pub tx_aborted_errors: AtomicU64,
-    pub rx_missed_errors: AtomicU64,
-    pub tx_window_errors: AtomicU64,
-    pub rx_length_errors: AtomicU64,
-    pub tx_heartbeat_errors: AtomicU64,
-    pub rx_overflow_errors: AtomicU64,
-    pub tx_underflow_errors: AtomicU64,
+orion_stats::counter_set! {
+    pub struct RTL8169NetworkStats => RTL8169NetworkStatsValues {
+        rx_packets,
+        tx_packets,
+        rx_bytes,
+        tx_bytes,
+        rx_errors,
+        tx_errors,
+        rx_dropped,
+        tx_dropped,
+        rx_fifo_errors,
+        tx_fifo_errors,
+        rx_frame_errors,
+        tx_carrier_errors,
+        rx_collision_errors,
+        tx_collision_errors,
+        rx_crc_errors,
+        tx_aborted_errors,
+        rx_missed_errors,
+        tx_window_errors,
+        rx_length_errors,
+        tx_heartbeat_errors,
+        rx_overflow_errors,
+        tx_underflow_errors,
+    }
 }
 
 // RTL8169 driver structure
@@ -552,28 +555,35 @@ impl RTL8169Driver {
 
     /// Check link status
     fn check_link_status(&mut self) -> DriverResult<()> {
-        // Read PHY status
-        let phy_status = self.read_phy_register(0x11)?;
+        // Speed and duplex as negotiated, or forced, on the PHY
+        let mode = mii::link_mode(self).map_err(|_| DriverError::IoError)?;
         
-        self.link_up = (phy_status & RTL8169_PHYSTATUS_LINK) != 0;
+        self.link_up = mode.is_some();
         
-        // Determine link speed
-        if (phy_status & RTL8169_PHYSTATUS_SPEED) != 0 {
-            self.link_speed = RTL8169LinkSpeed::Speed1000Mbps;
-        } else {
-            self.link_speed = RTL8169LinkSpeed::Speed100Mbps;
-        }
+        self.link_speed = match mode.map(|mode| mode.speed_mbps) {
+            Some(1000) => RTL8169LinkSpeed::Speed1000Mbps,
+            Some(100) => RTL8169LinkSpeed::Speed100Mbps,
+            Some(10) => RTL8169LinkSpeed::Speed10Mbps,
+            _ => RTL8169LinkSpeed::SpeedUnknown,
+        };
         
-        // Determine duplex mode
-        self.duplex_mode = if (phy_status & RTL8169_PHYSTATUS_DUPLEX) != 0 {
-            RTL8169DuplexMode::FullDuplex
-        } else {
-            RTL8169DuplexMode::HalfDuplex
+        self.duplex_mode = match mode {
+            Some(mode) if mode.full_duplex => RTL8169DuplexMode::FullDuplex,
+            Some(_) => RTL8169DuplexMode::HalfDuplex,
+            None => RTL8169DuplexMode::Unknown,
         };
         
         Ok(())
     }
 
+    /// Serve a PHY control request (see orion_phy::handle_control) and
+    /// pick up the link it leaves
+    pub fn phy_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let reply = handle_phy_control(self, request);
+        let _ = self.check_link_status();
+        reply
+    }
+
     /// Read PHY register
     fn read_phy_register(&self, reg: u8) -> DriverResult<u16> {
         // Write PHY address and register number
@@ -714,9 +724,41 @@ impl OrionDriver for RTL8169Driver {
         Ok(())
     }
     
-    fn handle_message(&mut self, _message: ReceivedMessage, _ipc: &mut dyn IpcInterface) -> DriverResult<()> {
-        // Handle driver-specific messages
-        Ok(())
+    fn handle_message(&mut self, message: ReceivedMessage, ipc: &mut dyn IpcInterface) -> DriverResult<()> {
+        match message {
+            ReceivedMessage::IoRequest(io_msg)
+                if matches!(io_msg.request_type, IoRequestType::Ioctl) && io_msg.length == PHY_IOCTL_CONTROL =>
+            {
+                // PHY requests answer with data rather than a length
+                let reply = self.phy_control(&io_msg.data);
+                ipc.send_response(io_msg.header.sequence, 0, &reply)
+            }
+            // Handle driver-specific messages
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Mdio for RTL8169Driver {
+    fn read(&mut self, register: u8) -> Result<u16, PhyError> {
+        self.read_phy_register(register).map_err(|_| PhyError::Bus)
+    }
+
+    fn write(&mut self, register: u8, value: u16) -> Result<(), PhyError> {
+        self.write_phy_register(register, value).map_err(|_| PhyError::Bus)
+    }
+}
+
+// Receive goes through one ring buffer and transmit through the four
+// slots of TXADDR0-3; checksums and VLAN tags are left to software
+impl Reconfigurable for RTL8169Driver {
+    fn ring_limits(&self) -> RingLimits {
+        let (rings, offloads) = self.ring_config();
+        RingLimits::fixed(rings, offloads)
+    }
+
+    fn ring_config(&self) -> (RingConfig, Offloads) {
+        (RingConfig { rx: 1, tx: 4 }, Offloads::NONE)
     }
 }
 
@@ -749,8 +791,8 @@ impl NetworkDriver for RTL8169Driver {
         self.tx_head = next_tx;
         
         // Update statistics
-        self.stats.tx_packets.fetch_add(1, Ordering::Relaxed);
-        self.stats.tx_bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+        self.stats.tx_packets().inc();
+        self.stats.tx_bytes().add(data.len() as u64);
         
         Ok(data.len())
     }
@@ -783,8 +825,8 @@ impl NetworkDriver for RTL8169Driver {
         self.mmio.write_u16(RTL8169_RXBUFTAIL, new_tail)?;
         
         // Update statistics
-        self.stats.rx_packets.fetch_add(1, Ordering::Relaxed);
-        self.stats.rx_bytes.fetch_add(length as u64, Ordering::Relaxed);
+        self.stats.rx_packets().inc();
+        self.stats.rx_bytes().add(length as u64);
         
         Ok(length)
     }
@@ -825,14 +867,14 @@ impl RTL8169Driver {
     /// Handle receive error interrupt
     fn handle_receive_error_interrupt(&mut self) -> DriverResult<()> {
         // Handle receive errors
-        self.stats.rx_errors.fetch_add(1, Ordering::Relaxed);
+        self.stats.rx_errors().inc();
         Ok(())
     }
     
     /// Handle transmit error interrupt
     fn handle_transmit_error_interrupt(&mut self) -> DriverResult<()> {
         // Handle transmit errors
-        self.stats.tx_errors.fetch_add(1, Ordering::Relaxed);
+        self.stats.tx_errors().inc();
         Ok(())
     }
 }
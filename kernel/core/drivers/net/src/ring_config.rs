@@ -0,0 +1,215 @@
+/*
+ * Orion Operating System - Ring and Offload Reconfiguration
+ *
+ * Descriptor ring sizes and offloads changed while an interface is up.
+ * A request is checked against the limits the driver found when it probed
+ * the device; the driver then quiesces both rings, replaces them and
+ * programs the device with them and the offloads, and resumes. Only DMA
+ * stops meanwhile: MAC and PHY are left alone, so the link stays up and
+ * link event subscribers hear nothing. Frames arriving while the receiver
+ * is off are dropped by the device as in an overrun, and frames queued
+ * for transmission go out on the new ring.
+ *
+ * Drivers whose rings cannot change without a device reset report fixed
+ * limits, accepting only the configuration they run with.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::ops::BitOr;
+
+use orion_driver::{DriverError, DriverResult, NetworkDriver};
+
+/// Descriptors in the receive and transmit rings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingConfig {
+    pub rx: u16,
+    pub tx: u16,
+}
+
+/// A set of offloads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Offloads(u32);
+
+impl Offloads {
+    pub const NONE: Offloads = Offloads(0);
+    /// Checksums of received frames verified by the device
+    pub const RX_CHECKSUM: Offloads = Offloads(1 << 0);
+    /// Checksums of transmitted frames filled in by the device
+    pub const TX_CHECKSUM: Offloads = Offloads(1 << 1);
+    pub const TSO: Offloads = Offloads(1 << 2);
+    /// VLAN tags removed from received frames
+    pub const VLAN_STRIP: Offloads = Offloads(1 << 3);
+    /// Frames of VLANs not joined dropped by the device
+    pub const VLAN_FILTER: Offloads = Offloads(1 << 4);
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Offloads(bits)
+    }
+
+    pub const fn contains(self, other: Offloads) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Offloads of self not in other
+    pub const fn difference(self, other: Offloads) -> Offloads {
+        Offloads(self.0 & !other.0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Offloads {
+    type Output = Offloads;
+
+    fn bitor(self, other: Offloads) -> Offloads {
+        Offloads(self.0 | other.0)
+    }
+}
+
+/// Why a configuration was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingError {
+    RxSize,
+    TxSize,
+    /// Offloads the device does not have
+    Offloads(Offloads),
+}
+
+impl From<RingError> for DriverError {
+    fn from(error: RingError) -> Self {
+        match error {
+            RingError::RxSize | RingError::TxSize => DriverError::InvalidParameter,
+            RingError::Offloads(_) => DriverError::Unsupported,
+        }
+    }
+}
+
+/// What a device accepts, found when it is probed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingLimits {
+    pub min_rx: u16,
+    pub max_rx: u16,
+    pub min_tx: u16,
+    pub max_tx: u16,
+    /// Ring sizes are a multiple of this, for the ring alignment the
+    /// device requires
+    pub granularity: u16,
+    pub offloads: Offloads,
+}
+
+impl RingLimits {
+    /// Limits of a device whose rings and offloads cannot change
+    pub fn fixed(rings: RingConfig, offloads: Offloads) -> Self {
+        RingLimits {
+            min_rx: rings.rx,
+            max_rx: rings.rx,
+            min_tx: rings.tx,
+            max_tx: rings.tx,
+            granularity: 1,
+            offloads,
+        }
+    }
+
+    pub fn check(&self, rings: RingConfig, offloads: Offloads) -> Result<(), RingError> {
+        let fits = |size: u16, min: u16, max: u16| (min..=max).contains(&size) && size % self.granularity.max(1) == 0;
+        if !fits(rings.rx, self.min_rx, self.max_rx) {
+            return Err(RingError::RxSize);
+        }
+        if !fits(rings.tx, self.min_tx, self.max_tx) {
+            return Err(RingError::TxSize);
+        }
+        let missing = offloads.difference(self.offloads);
+        if !missing.is_empty() {
+            return Err(RingError::Offloads(missing));
+        }
+        Ok(())
+    }
+}
+
+/// Network drivers whose rings and offloads can change while they run.
+/// Those with fixed limits need only the first two methods: the rest is
+/// never called for the configuration they already have
+pub trait Reconfigurable: NetworkDriver {
+    fn ring_limits(&self) -> RingLimits;
+
+    /// Rings and offloads in use
+    fn ring_config(&self) -> (RingConfig, Offloads);
+
+    /// Stop DMA on both rings once the device is done with the frames
+    /// handed to it, leaving the link up. On failure nothing has stopped
+    fn quiesce(&mut self) -> DriverResult<()> {
+        Err(DriverError::Unsupported)
+    }
+
+    /// Replace both rings and program the device with them and the
+    /// offloads; the rings are quiesced
+    fn reprogram(&mut self, _rings: RingConfig, _offloads: Offloads) -> DriverResult<()> {
+        Err(DriverError::Unsupported)
+    }
+
+    /// Restart DMA, on whichever rings are programmed
+    fn resume(&mut self) -> DriverResult<()> {
+        Err(DriverError::Unsupported)
+    }
+}
+
+/// Move a running driver to new rings and offloads. Nothing happens when
+/// they are the ones in use; if reprogramming fails the previous ones are
+/// restored before DMA resumes, and the error is returned
+pub fn reconfigure(driver: &mut dyn Reconfigurable, rings: RingConfig, offloads: Offloads) -> DriverResult<()> {
+    driver.ring_limits().check(rings, offloads)?;
+    let (current_rings, current_offloads) = driver.ring_config();
+    if (current_rings, current_offloads) == (rings, offloads) {
+        return Ok(());
+    }
+
+    driver.quiesce()?;
+    let result = driver.reprogram(rings, offloads);
+    if result.is_err() {
+        driver.reprogram(current_rings, current_offloads)?;
+    }
+    driver.resume()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_against_device_limits() {
+        let limits = RingLimits {
+            min_rx: 48,
+            max_rx: 4096,
+            min_tx: 48,
+            max_tx: 1024,
+            granularity: 8,
+            offloads: Offloads::RX_CHECKSUM | Offloads::TX_CHECKSUM | Offloads::TSO,
+        };
+        assert_eq!(limits.check(RingConfig { rx: 4096, tx: 48 }, Offloads::TSO), Ok(()));
+        assert_eq!(limits.check(RingConfig { rx: 4104, tx: 256 }, Offloads::NONE), Err(RingError::RxSize));
+        assert_eq!(limits.check(RingConfig { rx: 256, tx: 2048 }, Offloads::NONE), Err(RingError::TxSize));
+        // Not a multiple of the granularity
+        assert_eq!(limits.check(RingConfig { rx: 100, tx: 256 }, Offloads::NONE), Err(RingError::RxSize));
+        assert_eq!(
+            limits.check(RingConfig { rx: 256, tx: 256 }, Offloads::TSO | Offloads::VLAN_STRIP),
+            Err(RingError::Offloads(Offloads::VLAN_STRIP))
+        );
+
+        let fixed = RingLimits::fixed(RingConfig { rx: 64, tx: 4 }, Offloads::NONE);
+        assert_eq!(fixed.check(RingConfig { rx: 64, tx: 4 }, Offloads::NONE), Ok(()));
+        assert_eq!(fixed.check(RingConfig { rx: 64, tx: 8 }, Offloads::NONE), Err(RingError::TxSize));
+        assert!(matches!(DriverError::from(RingError::Offloads(Offloads::TSO)), DriverError::Unsupported));
+    }
+}
@@ -14,11 +14,22 @@
 #![no_std]
 #![no_main]
 
+extern crate alloc;
+
 use orion_driver::{
     NetworkDriver, DeviceInfo, DriverError, DriverInfo, DriverResult, OrionDriver,
-    MmioAccessor, MmioPermissions, LinkStatus, NetworkStats,
+    MmioAccessor, MmioPermissions, LinkStatus,
     MessageLoop, ReceivedMessage, IoRequestType,
 };
+use orion_netstats::{ErrorKind, NetworkStats};
+use orion_pktfilter::control::CTRL_EINVAL;
+use orion_pktfilter::{handle_control, handle_multicast, MulticastFilter, PacketFilter, Verdict};
+use orion_phy::mii::{self, LinkMode, MII_ADVERTISE, MII_BMCR, MII_BMSR, MII_EXPANSION, MII_LPA};
+use orion_phy::{handle_control as handle_phy_control, Mdio, PhyError};
+use orion_sys::clock_get;
+use alloc::vec::Vec;
+
+use super::ring_config::{Offloads, Reconfigurable, RingConfig, RingLimits};
 
 /// Realtek RTL8139 Network Driver
 pub struct Rtl8139Driver {
@@ -30,6 +41,16 @@ pub struct Rtl8139Driver {
     current_tx_buffer: usize,
     stats: NetworkStats,
     link_up: bool,
+    /// Mode the internal PHY reports, None until it has been read
+    link_mode: Option<LinkMode>,
+    rx_filter: Option<PacketFilter>,
+    multicast: MulticastFilter,
+}
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
 }
 
 // RTL8139 register offsets
@@ -198,6 +219,10 @@ impl OrionDriver for Rtl8139Driver {
                        (0x06 << RTL8139_RX_CONFIG_FTH_SHIFT);    // 1024 byte threshold
         mmio.write_u32(RTL8139_RXCONFIG, rx_config)?;
         
+        // Every multicast group passes the hash filter until the server programs its list
+        mmio.write_u32(RTL8139_MAR0, 0xFFFF_FFFF)?;
+        mmio.write_u32(RTL8139_MAR0 + 4, 0xFFFF_FFFF)?;
+        
         // Configure TX: default values, 1024 byte FIFO threshold
         let tx_config = (0x06 << RTL8139_TX_FIFO_THRESH_SHIFT) | // 1024 byte threshold
                        (0x07 << 8) |               // Max DMA burst
@@ -226,6 +251,9 @@ impl OrionDriver for Rtl8139Driver {
             current_tx_buffer: 0,
             stats: NetworkStats::default(),
             link_up,
+            link_mode: None,
+            rx_filter: None,
+            multicast: MulticastFilter::default(),
         })
     }
     
@@ -245,18 +273,19 @@ impl OrionDriver for Rtl8139Driver {
         }
         
         if isr & RTL8139_INT_RX_ERR != 0 {
-            // RX error
-            self.stats.rx_errors += 1;
+            // RX error: the chip reports CRC and alignment failures here
+            self.stats.record_rx_error(ErrorKind::Crc);
         }
         
         if isr & RTL8139_INT_TX_ERR != 0 {
             // TX error
-            self.stats.tx_errors += 1;
+            self.stats.record_tx_error(ErrorKind::Other);
         }
         
         if isr & RTL8139_INT_RXBUF_OVERFLOW != 0 {
             // RX buffer overflow
-            self.stats.rx_dropped += 1;
+            self.stats.record_rx_error(ErrorKind::RingOverflow);
+            self.stats.record_rx_drop(0);
         }
         
         if isr & (RTL8139_INT_RXFIFO_UNDERRUN | RTL8139_INT_PCIERR) != 0 {
@@ -319,8 +348,7 @@ impl NetworkDriver for Rtl8139Driver {
         // Move to next TX buffer
         self.current_tx_buffer = (self.current_tx_buffer + 1) % self.tx_buffer_count;
         
-        self.stats.tx_packets += 1;
-        self.stats.tx_bytes += packet.len() as u64;
+        self.stats.record_tx(0, packet.len());
         
         Ok(packet.len())
     }
@@ -363,9 +391,16 @@ impl NetworkDriver for Rtl8139Driver {
         let new_tail = (rx_tail + packet_length as u16) % 0x1000;
         self.mmio.write_u16(RTL8139_RXBUFTAIL, new_tail)?;
         
+        // Early filter; the single RX ring delivers redirects as is
+        if let Some(filter) = self.rx_filter.as_mut() {
+            if filter.run(&buffer[..packet_length], monotonic_ns()) == Verdict::Drop {
+                self.stats.record_rx_drop(0);
+                return Err(DriverError::NoData);
+            }
+        }
+        
         // Update statistics
-        self.stats.rx_packets.fetch_add(1, Ordering::Relaxed);
-        self.stats.rx_bytes.fetch_add(packet_length as u64, Ordering::Relaxed);
+        self.stats.record_rx(0, packet_length);
         
         Ok(packet_length)
     }
@@ -396,8 +431,9 @@ impl NetworkDriver for Rtl8139Driver {
     
     fn link_status(&self) -> LinkStatus {
         if self.link_up {
-            // RTL8139 is 100Mbps Fast Ethernet, always full duplex when link is up
-            LinkStatus::Up { speed_mbps: 100, duplex: true }
+            // Before the PHY has been read, assume the usual 100 Mbps full duplex
+            let mode = self.link_mode.unwrap_or(LinkMode { speed_mbps: 100, full_duplex: true });
+            LinkStatus::Up { speed_mbps: mode.speed_mbps, duplex: mode.full_duplex }
         } else {
             LinkStatus::Down
         }
@@ -425,11 +461,23 @@ impl NetworkDriver for Rtl8139Driver {
     }
 }
 
+// The chip has one receive ring buffer and four transmit slots, and no
+// offloads
+impl Reconfigurable for Rtl8139Driver {
+    fn ring_limits(&self) -> RingLimits {
+        let (rings, offloads) = self.ring_config();
+        RingLimits::fixed(rings, offloads)
+    }
+
+    fn ring_config(&self) -> (RingConfig, Offloads) {
+        (RingConfig { rx: 1, tx: self.tx_buffer_count as u16 }, Offloads::NONE)
+    }
+}
+
 impl Rtl8139Driver {
     fn handle_rx_interrupt(&mut self) -> DriverResult<()> {
         // TODO: Process received packets from RX buffer
-        self.stats.rx_packets += 1;
-        self.stats.rx_bytes += 64; // Simulate packet size
+        self.stats.record_rx(0, 64); // Simulate packet size
         Ok(())
     }
     
@@ -438,11 +486,69 @@ impl Rtl8139Driver {
         Ok(())
     }
     
+    /// Serve a packet filter control request (see orion_pktfilter::handle_control)
+    pub fn filter_control(&mut self, request: &[u8]) -> Vec<u8> {
+        handle_control(&mut self.rx_filter, request, 1)
+    }
+    
+    /// Serve a multicast list request (see orion_pktfilter::handle_multicast)
+    /// and load the list into the 64-bit hash filter; frames of groups
+    /// sharing a hash bit still pass and are dropped by the IP layer
+    pub fn multicast_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let reply = handle_multicast(&mut self.multicast, request);
+        let table = self.multicast.hash_table();
+        let written = self.mmio.write_u32(RTL8139_MAR0, table[0])
+            .and_then(|_| self.mmio.write_u32(RTL8139_MAR0 + 4, table[1]));
+        match written {
+            Ok(()) => reply,
+            Err(_) => CTRL_EINVAL.to_le_bytes().to_vec(),
+        }
+    }
+    
+    /// Serve a PHY control request (see orion_phy::handle_control); the
+    /// internal PHY has no identifier registers and no cable tester
+    pub fn phy_control(&mut self, request: &[u8]) -> Vec<u8> {
+        let reply = handle_phy_control(self, request);
+        let _ = self.handle_link_change();
+        reply
+    }
+    
     fn handle_link_change(&mut self) -> DriverResult<()> {
         let media_status = self.mmio.read_u8(RTL8139_MEDIASTAT)?;
         self.link_up = (media_status & RTL8139_MEDIASTAT_LINK) != 0;
+        self.link_mode = mii::link_mode(self).map_err(|_| DriverError::IoError)?;
         Ok(())
     }
+    
+    /// Offset of the internal PHY register mapped at `register`
+    fn phy_register(register: u8) -> Result<usize, PhyError> {
+        match register {
+            MII_BMCR => Ok(RTL8139_BASICMODECONTROL),
+            MII_BMSR => Ok(RTL8139_BASICMODESTATUS),
+            MII_ADVERTISE => Ok(RTL8139_NWAYADVERT),
+            MII_LPA => Ok(RTL8139_NWAYLPAR),
+            MII_EXPANSION => Ok(RTL8139_NWAYEXPANSION),
+            _ => Err(PhyError::NoRegister),
+        }
+    }
+}
+
+/// The RTL8139 PHY is not behind MDIO; its MII registers are mapped
+/// into the register window
+impl Mdio for Rtl8139Driver {
+    fn read(&mut self, register: u8) -> Result<u16, PhyError> {
+        let offset = Self::phy_register(register)?;
+        self.mmio.read_u16(offset).map_err(|_| PhyError::Bus)
+    }
+    
+    fn write(&mut self, register: u8, value: u16) -> Result<(), PhyError> {
+        let offset = Self::phy_register(register)?;
+        // BMCR is write protected like the configuration registers
+        let written = self.mmio.write_u8(RTL8139_CFG9346, RTL8139_CFG9346_UNLOCK)
+            .and_then(|_| self.mmio.write_u16(offset, value));
+        let locked = self.mmio.write_u8(RTL8139_CFG9346, RTL8139_CFG9346_LOCK);
+        written.and(locked).map_err(|_| PhyError::Bus)
+    }
 }
 
 /// Driver entry point
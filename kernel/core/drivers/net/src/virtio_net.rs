@@ -16,9 +16,27 @@
 
 use orion_driver::{
     NetworkDriver, DeviceInfo, DriverError, DriverInfo, DriverResult, OrionDriver,
-    MmioAccessor, MmioPermissions, LinkStatus, NetworkStats, BusType,
+    MmioAccessor, MmioPermissions, LinkStatus, BusType,
     MessageLoop, ReceivedMessage, IoRequestType, virtio_constants::*,
 };
+use orion_ipc::IpcChannel;
+use orion_netstats::{ErrorKind, NetworkStats, MAX_QUEUES};
+use orion_pktfilter::{
+    handle_control, handle_multicast, handle_rx_mode, MulticastFilter, PacketFilter, RxMode, RxModeChange, Verdict,
+    FILTER_IOCTL_CONTROL, FILTER_IOCTL_MULTICAST, FILTER_IOCTL_RX_MODE,
+};
+use orion_pktfilter::multicast::MAX_MULTICAST_ADDRESSES;
+use orion_probe::points::{
+    DRIVER_HANDLE_IRQ, NET_RECEIVE_PACKET, NET_SEND_PACKET, NET_SET_MAC_ADDRESS, NET_SET_PROMISCUOUS,
+};
+use orion_probe::{probe, Probes, PROBE_IOCTL_CONTROL};
+use orion_rxpool::{
+    shm_flags, DeliverReply, NodePool, PoolLayout, RxCompletion, RxPoolRequest, Watermarks, PAGE_BUFFER_SIZE,
+};
+use orion_rxpool::pool::FLAG_CHECKSUM_VALID;
+use orion_sys::clock_get;
+
+use super::ring_config::{Offloads, Reconfigurable, RingConfig, RingLimits};
 
 /// VirtIO Network Device Driver
 pub struct VirtioNetDriver {
@@ -34,8 +52,54 @@ pub struct VirtioNetDriver {
     tx_queue: Option<VirtioQueue>,
     rx_queue_memory: Option<*mut u8>,
     tx_queue_memory: Option<*mut u8>,
+    rx_pool: Option<RxPoolBinding>,
+    rx_filter: Option<PacketFilter>,
+    // Loaded into the device MAC table when it has one, and checked in
+    // software in any case
+    multicast: MulticastFilter,
+    ctrl_queue: Option<ControlQueue>,
+    rx_mode: RxMode,
+}
+
+/// Control virtqueue. Commands are run one at a time: the header, the
+/// command data and the acknowledgement the device writes back all live
+/// in `buffer`, which does not move while the driver waits for the reply.
+struct ControlQueue {
+    queue: VirtioQueue,
+    index: u16,
+    buffer: [u8; CTRL_BUFFER_SIZE],
+}
+
+/// Receive pool shared with the network server: RX descriptors point at
+/// pool buffers and filled ones are passed on by index, never copied
+struct RxPoolBinding {
+    pool: NodePool,
+    id: u32,
+    mapping: u64,
+    net: IpcChannel,
+    /// Pool buffer behind each posted RX descriptor
+    posted: [u32; RX_POOL_BUFFERS as usize],
+    /// The server asked for backpressure: buffers are not reposted
+    throttled: bool,
+}
+
+// Receive pool geometry: one page-sized buffer per RX descriptor, room
+// for the VirtIO header and a full Ethernet frame
+const RX_POOL_BUFFERS: u32 = 256;
+
+// Consumer queues a filter may redirect pool frames to: the queue is
+// carried in the completion for the network server to dispatch on
+const RX_FILTER_QUEUES: u8 = MAX_QUEUES as u8;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
 }
 
+/// Trait method probes, switched by orion-trace
+static PROBES: Probes = Probes::new(monotonic_ns);
+
 // VirtIO constants are imported from orion_driver::virtio_constants - no duplication
 
 // VirtIO Net features
@@ -51,7 +115,58 @@ const VIRTIO_NET_F_STATUS: u64 = 1 << 16;
 const VIRTIO_NET_F_CTRL_VQ: u64 = 1 << 17;
 const VIRTIO_NET_F_CTRL_RX: u64 = 1 << 18;
 const VIRTIO_NET_F_CTRL_VLAN: u64 = 1 << 19;
+const VIRTIO_NET_F_GUEST_ANNOUNCE: u64 = 1 << 21;
 const VIRTIO_NET_F_MQ: u64 = 1 << 22;
+const VIRTIO_NET_F_CTRL_MAC_ADDR: u64 = 1 << 23;
+
+// Features carried over the control virtqueue
+const VIRTIO_NET_CTRL_FEATURES: u64 = VIRTIO_NET_F_CTRL_VQ | VIRTIO_NET_F_CTRL_RX | VIRTIO_NET_F_CTRL_VLAN |
+    VIRTIO_NET_F_GUEST_ANNOUNCE | VIRTIO_NET_F_CTRL_MAC_ADDR;
+
+// Device configuration: status word and, with MQ, the queue pair count
+const VIRTIO_NET_CONFIG_STATUS: usize = 6;
+const VIRTIO_NET_CONFIG_MAX_QUEUE_PAIRS: usize = 8;
+const VIRTIO_NET_S_LINK_UP: u16 = 1;
+const VIRTIO_NET_S_ANNOUNCE: u16 = 2;
+
+// Control virtqueue classes and their commands
+const VIRTIO_NET_CTRL_RX: u8 = 0;
+const VIRTIO_NET_CTRL_RX_PROMISC: u8 = 0;
+const VIRTIO_NET_CTRL_RX_ALLMULTI: u8 = 1;
+const VIRTIO_NET_CTRL_MAC: u8 = 1;
+const VIRTIO_NET_CTRL_MAC_TABLE_SET: u8 = 0;
+const VIRTIO_NET_CTRL_MAC_ADDR_SET: u8 = 1;
+const VIRTIO_NET_CTRL_VLAN: u8 = 2;
+const VIRTIO_NET_CTRL_VLAN_ADD: u8 = 0;
+const VIRTIO_NET_CTRL_VLAN_DEL: u8 = 1;
+const VIRTIO_NET_CTRL_ANNOUNCE: u8 = 3;
+const VIRTIO_NET_CTRL_ANNOUNCE_ACK: u8 = 0;
+const VIRTIO_NET_OK: u8 = 0;
+
+const CTRL_QUEUE_SIZE: u16 = 16;
+const CTRL_BUFFER_SIZE: usize = 512;
+/// The device answers control commands right away; this only guards
+/// against a broken one
+const CTRL_TIMEOUT_NS: u64 = 100_000_000;
+
+/// Ethernet frames are padded to this length
+const MIN_FRAME_SIZE: usize = 60;
+
+// Receive mode reply status codes for commands the device refused
+const RX_MODE_EIO: i32 = -5;
+const RX_MODE_ETIMEDOUT: i32 = -110;
+const RX_MODE_EOPNOTSUPP: i32 = -95;
+
+fn rx_mode_status(error: DriverError) -> i32 {
+    match error {
+        DriverError::Unsupported => RX_MODE_EOPNOTSUPP,
+        DriverError::Timeout => RX_MODE_ETIMEDOUT,
+        _ => RX_MODE_EIO,
+    }
+}
+
+// VirtIO net header flag: the device validated the packet checksum
+const VIRTIO_NET_HDR_F_DATA_VALID: u8 = 2;
 
 // Network packet header for VirtIO
 #[repr(C, packed)]
@@ -192,6 +307,11 @@ impl VirtioQueue {
     }
     
     fn check_used(&mut self) -> Option<u16> {
+        self.next_used().map(|(id, _)| id)
+    }
+
+    /// Next used descriptor and the number of bytes the device wrote to it
+    fn next_used(&mut self) -> Option<(u16, u32)> {
         unsafe {
             let used_idx = (*self.used).idx;
             if used_idx != self.last_used_idx {
@@ -199,9 +319,10 @@ impl VirtioQueue {
                     (*self.used).ring.as_ptr(),
                     self.size as usize
                 );
-                let id = ring[self.last_used_idx as usize].id;
+                let elem = &ring[self.last_used_idx as usize];
+                let (id, len) = (elem.id, elem.len);
                 self.last_used_idx = (self.last_used_idx + 1) % self.size;
-                Some(id as u16)
+                Some((id as u16, len))
             } else {
                 None
             }
@@ -293,6 +414,11 @@ impl OrionDriver for VirtioNetDriver {
         if device_features & (VIRTIO_NET_F_HOST_TSO6 as u32) != 0 {
             driver_features |= VIRTIO_NET_F_HOST_TSO6 as u32;
         }
+        // Receive mode, VLAN filter, MAC and announce commands all go
+        // through the control queue
+        if device_features & (VIRTIO_NET_F_CTRL_VQ as u32) != 0 {
+            driver_features |= device_features & (VIRTIO_NET_CTRL_FEATURES as u32);
+        }
         
         // Write driver features
         mmio.write_u32(VIRTIO_MMIO_DRIVER_FEATURES, driver_features)?;
@@ -348,6 +474,9 @@ impl OrionDriver for VirtioNetDriver {
         mmio.write_u64(0x040, tx_queue_memory as u64)?; // Queue address
         mmio.write_u32(VIRTIO_MMIO_QUEUE_READY, 1)?; // Mark queue ready
         
+        // Set up the control queue (queue 2) if negotiated
+        let ctrl_queue = Self::setup_control_queue(&mmio, driver_features as u64)?;
+        
         // Store queue memory addresses for later use
         let rx_queue = Some(VirtioQueue::new(rx_queue_memory, rx_queue_size)?);
         let tx_queue = Some(VirtioQueue::new(tx_queue_memory, tx_queue_size)?);
@@ -357,7 +486,7 @@ impl OrionDriver for VirtioNetDriver {
                       VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER | 
                       VIRTIO_STATUS_FEATURES_OK | VIRTIO_STATUS_DRIVER_OK)?;
         
-        Ok(VirtioNetDriver {
+        let mut driver = VirtioNetDriver {
             device,
             mmio,
             mac_address,
@@ -370,34 +499,52 @@ impl OrionDriver for VirtioNetDriver {
             tx_queue,
             rx_queue_memory: Some(rx_queue_memory),
             tx_queue_memory: Some(tx_queue_memory),
-        })
-    }
-    
-    fn handle_irq(&mut self) -> DriverResult<()> {
-        // Read interrupt status
-        let status = self.mmio.read_u32(VIRTIO_MMIO_INTERRUPT_STATUS)?;
-        
-        if status & 1 != 0 {
-            // Queue interrupt - process RX/TX completions
-            self.handle_rx_interrupt()?;
-            self.handle_tx_interrupt()?;
-        }
+            rx_pool: None,
+            rx_filter: None,
+            multicast: MulticastFilter::default(),
+            ctrl_queue,
+            rx_mode: RxMode::new(mac_address),
+        };
         
-        if status & 2 != 0 {
-            // Configuration change interrupt
-            self.handle_config_change()?;
+        // Without a receive pool frames are copied out by receive_packet()
+        if driver.attach_rx_pool().is_ok() {
+            driver.refill_rx_queue()?;
         }
         
-        // Acknowledge interrupts
-        self.mmio.write_u32(VIRTIO_MMIO_INTERRUPT_ACK, status)?;
+        // A device that refuses keeps the receive mode it was reset to
+        let _ = driver.program_rx_mode();
         
-        Ok(())
+        Ok(driver)
+    }
+    
+    fn handle_irq(&mut self) -> DriverResult<()> {
+        probe!(PROBES, DRIVER_HANDLE_IRQ, [], {
+            // Read interrupt status
+            let status = self.mmio.read_u32(VIRTIO_MMIO_INTERRUPT_STATUS)?;
+
+            if status & 1 != 0 {
+                // Queue interrupt - process RX/TX completions
+                self.handle_rx_interrupt()?;
+                self.handle_tx_interrupt()?;
+            }
+
+            if status & 2 != 0 {
+                // Configuration change interrupt
+                self.handle_config_change()?;
+            }
+
+            // Acknowledge interrupts
+            self.mmio.write_u32(VIRTIO_MMIO_INTERRUPT_ACK, status)?;
+
+            Ok(())
+        })
     }
     
     fn shutdown(&mut self) -> DriverResult<()> {
         // Reset device status
         self.mmio.write_u32(VIRTIO_MMIO_STATUS, 0)?;
         self.link_up = false;
+        self.detach_rx_pool();
         Ok(())
     }
     
@@ -411,126 +558,181 @@ impl OrionDriver for VirtioNetDriver {
     }
 }
 
+// Queue sizes are fixed once the device is live, and the offloads by the
+// features negotiated: changing either takes a device reset, which would
+// drop the link
+impl Reconfigurable for VirtioNetDriver {
+    fn ring_limits(&self) -> RingLimits {
+        let (rings, offloads) = self.ring_config();
+        RingLimits::fixed(rings, offloads)
+    }
+
+    fn ring_config(&self) -> (RingConfig, Offloads) {
+        let mut offloads = Offloads::NONE;
+        for (feature, offload) in [
+            (VIRTIO_NET_F_GUEST_CSUM, Offloads::RX_CHECKSUM),
+            (VIRTIO_NET_F_CSUM, Offloads::TX_CHECKSUM),
+            (VIRTIO_NET_F_HOST_TSO4, Offloads::TSO),
+            (VIRTIO_NET_F_CTRL_VLAN, Offloads::VLAN_FILTER),
+        ] {
+            if self.features & feature != 0 {
+                offloads = offloads | offload;
+            }
+        }
+        (RingConfig { rx: self.rx_queue_size, tx: self.tx_queue_size }, offloads)
+    }
+}
+
 impl NetworkDriver for VirtioNetDriver {
     fn send_packet(&mut self, packet: &[u8]) -> DriverResult<usize> {
-        if packet.len() > 1514 {
-            return Err(DriverError::MemoryError); // MTU exceeded
-        }
-        
-        if !self.link_up {
-            return Err(DriverError::DeviceNotFound);
-        }
-        
-        //   packet transmission implementation via virtqueue
-        // This involves:
-        // 1. Allocating descriptors for the packet
-        // 2. Setting up the packet data in memory
-        // 3. Adding descriptors to the available ring
-        // 4. Notifying the device
-        // 5. Waiting for completion
-        
-        if let Some(ref mut tx_queue) = self.tx_queue {
-            // Allocate memory for packet data first
-            let packet_memory = self.allocate_packet_memory(packet.len())?;
-            
-            // Copy packet data to allocated memory
-            unsafe {
-                let packet_slice = core::slice::from_raw_parts_mut(packet_memory, packet.len());
-                packet_slice.copy_from_slice(packet);
+        probe!(PROBES, NET_SEND_PACKET, [packet], {
+            if packet.len() > 1514 {
+                return Err(DriverError::MemoryError); // MTU exceeded
             }
-            
-            // Allocate descriptor for packet transmission
-            let desc_head = tx_queue.alloc_desc(1).ok_or(DriverError::General)?;
-            let desc = unsafe { &mut *tx_queue.desc.offset(desc_head as isize) };
-            
-            // Set up descriptor
-            desc.addr = packet_memory as u64;
-            desc.len = packet.len() as u32;
-            desc.flags = 0; // Write-only
-            desc.next = 0;
-            
-            // Add to available ring
-            tx_queue.add_to_avail(desc_head);
-            
-            // Notify device
-            self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 1)?; // Queue 1 for TX
-            
-            // Wait for completion
-            let mut timeout = 1000000; // 1 second timeout
-            while timeout > 0 {
-                if let Some(completed_id) = tx_queue.check_used() {
-                    if completed_id == desc_head {
-                        break;
+
+            if !self.link_up {
+                return Err(DriverError::DeviceNotFound);
+            }
+
+            //   packet transmission implementation via virtqueue
+            // This involves:
+            // 1. Allocating descriptors for the packet
+            // 2. Setting up the packet data in memory
+            // 3. Adding descriptors to the available ring
+            // 4. Notifying the device
+            // 5. Waiting for completion
+
+            if let Some(ref mut tx_queue) = self.tx_queue {
+                // Allocate memory for packet data first
+                let packet_memory = self.allocate_packet_memory(packet.len())?;
+
+                // Copy packet data to allocated memory
+                unsafe {
+                    let packet_slice = core::slice::from_raw_parts_mut(packet_memory, packet.len());
+                    packet_slice.copy_from_slice(packet);
+                }
+
+                // Allocate descriptor for packet transmission
+                let desc_head = tx_queue.alloc_desc(1).ok_or(DriverError::General)?;
+                let desc = unsafe { &mut *tx_queue.desc.offset(desc_head as isize) };
+
+                // Set up descriptor
+                desc.addr = packet_memory as u64;
+                desc.len = packet.len() as u32;
+                desc.flags = 0; // Write-only
+                desc.next = 0;
+
+                // Add to available ring
+                tx_queue.add_to_avail(desc_head);
+
+                // Notify device
+                self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 1)?; // Queue 1 for TX
+
+                // Wait for completion
+                let mut timeout = 1000000; // 1 second timeout
+                while timeout > 0 {
+                    if let Some(completed_id) = tx_queue.check_used() {
+                        if completed_id == desc_head {
+                            break;
+                        }
+                    }
+                    timeout -= 1;
+                    for _ in 0..1000 {
+                        core::hint::spin_loop();
                     }
                 }
-                timeout -= 1;
-                for _ in 0..1000 {
-                    core::hint::spin_loop();
+
+                if timeout == 0 {
+                    return Err(DriverError::Timeout);
                 }
+
+                // Free descriptor
+                tx_queue.free_desc(desc_head, 1);
+
+                // Update statistics
+                self.stats.record_tx(0, packet.len());
             }
-            
-            if timeout == 0 {
-                return Err(DriverError::Timeout);
-            }
-            
-            // Free descriptor
-            tx_queue.free_desc(desc_head, 1);
-            
-            // Update statistics
-            self.stats.tx_packets += 1;
-            self.stats.tx_bytes += packet.len() as u64;
-        }
-        
-        Ok(packet.len())
+
+            Ok(packet.len())
+        })
     }
     
     fn receive_packet(&mut self, buffer: &mut [u8]) -> DriverResult<usize> {
-        if !self.link_up {
-            return Err(DriverError::DeviceNotReady);
-        }
-        
-        //  packet reception implementation via virtqueue
-        // This involves:
-        // 1. Checking for available packets in the RX queue
-        // 2. Reading packet data from descriptors
-        // 3. Processing the received packet
-        // 4. Updating statistics
-        
-        if let Some(ref mut rx_queue) = self.rx_queue {
-            // Check for completed packets in the used ring
-            if let Some(completed_id) = rx_queue.check_used() {
-                // Get the descriptor that was used
-                let desc = unsafe { &*rx_queue.desc.offset(completed_id as isize) };
-                
-                // Read packet data from the descriptor
-                let packet_data = unsafe {
-                    core::slice::from_raw_parts(
-                        desc.addr as *const u8,
-                        desc.len as usize
-                    )
-                };
-                
-                // Copy packet data to the provided buffer
-                let copy_size = core::cmp::min(buffer.len(), packet_data.len());
-                buffer[..copy_size].copy_from_slice(&packet_data[..copy_size]);
-                
-                // Free the descriptor
-                rx_queue.free_desc(completed_id, 1);
-                
-                // Update statistics
-                self.stats.rx_packets += 1;
-                self.stats.rx_bytes += copy_size as u64;
-                
-                // Return the actual packet size
-                Ok(copy_size)
-            } else {
-                // No packets available
+        probe!(PROBES, NET_RECEIVE_PACKET, [buffer], {
+            if !self.link_up {
+                return Err(DriverError::DeviceNotReady);
+            }
+
+            // Frames go to the network server through the receive pool; polls
+            // keep ringing a throttled server so reception resumes with it
+            if let Some(binding) = self.rx_pool.as_ref() {
+                if binding.throttled {
+                    self.deliver_rx_buffers()?;
+                }
                 return Err(DriverError::NoData);
             }
-        } else {
-            // No RX queue available
-            return Err(DriverError::NoResources);
-        }
+
+            //  packet reception implementation via virtqueue
+            // This involves:
+            // 1. Checking for available packets in the RX queue
+            // 2. Reading packet data from descriptors
+            // 3. Processing the received packet
+            // 4. Updating statistics
+
+            if let Some(ref mut rx_queue) = self.rx_queue {
+                // Check for completed packets in the used ring
+                if let Some(completed_id) = rx_queue.check_used() {
+                    // Get the descriptor that was used
+                    let desc = unsafe { &*rx_queue.desc.offset(completed_id as isize) };
+
+                    // Read packet data from the descriptor
+                    let packet_data = unsafe {
+                        core::slice::from_raw_parts(
+                            desc.addr as *const u8,
+                            desc.len as usize
+                        )
+                    };
+
+                    // Groups the host did not join never reach the server
+                    if !self.multicast.accepts(packet_data) {
+                        rx_queue.free_desc(completed_id, 1);
+                        return Err(DriverError::NoData);
+                    }
+
+                    // Early filter; with a single RX queue redirects are delivered as is
+                    if let Some(filter) = self.rx_filter.as_mut() {
+                        if filter.run(packet_data, monotonic_ns()) == Verdict::Drop {
+                            rx_queue.free_desc(completed_id, 1);
+                            self.stats.record_rx_drop(0);
+                            return Err(DriverError::NoData);
+                        }
+                    }
+
+                    // Copy packet data to the provided buffer
+                    let copy_size = core::cmp::min(buffer.len(), packet_data.len());
+                    buffer[..copy_size].copy_from_slice(&packet_data[..copy_size]);
+
+                    // Free the descriptor
+                    rx_queue.free_desc(completed_id, 1);
+
+                    // Update statistics
+                    self.stats.record_rx(0, copy_size);
+                    if copy_size < packet_data.len() {
+                        // Truncated to fit the caller's buffer
+                        self.stats.record_rx_error(ErrorKind::Length);
+                    }
+
+                    // Return the actual packet size
+                    Ok(copy_size)
+                } else {
+                    // No packets available
+                    return Err(DriverError::NoData);
+                }
+            } else {
+                // No RX queue available
+                return Err(DriverError::NoResources);
+            }
+        })
     }
     
     fn mac_address(&self) -> [u8; 6] {
@@ -538,27 +740,11 @@ impl NetworkDriver for VirtioNetDriver {
     }
     
     fn set_promiscuous(&mut self, enabled: bool) -> DriverResult<()> {
-        // Implement promiscuous mode via control virtqueue
-        if self.features & VIRTIO_NET_F_CTRL_VQ == 0 {
-            return Err(DriverError::Unsupported);
-        }
-        
-        // Create control request for promiscuous mode
-        let mut control_req = [0u8; 8];
-        control_req[0] = 0x01; // VIRTIO_NET_CTRL_RX
-        control_req[1] = 0x00; // VIRTIO_NET_CTRL_RX_PROMISC
-        control_req[2] = if enabled { 0x01 } else { 0x00 }; // Enable/disable
-        control_req[3] = 0x00; // Padding
-        
-        // Send control request via control virtqueue
-        self.send_control_request(&control_req)?;
-        
-        // Update internal state
-        if enabled {
-            self.stats.rx_packets += 1; // Track promiscuous mode activation
-        }
-        
-        Ok(())
+        probe!(PROBES, NET_SET_PROMISCUOUS, [enabled], {
+            self.set_rx_flag(VIRTIO_NET_CTRL_RX_PROMISC, enabled)?;
+            self.rx_mode.promiscuous = enabled;
+            Ok(())
+        })
     }
     
     fn link_status(&self) -> LinkStatus {
@@ -578,18 +764,21 @@ impl NetworkDriver for VirtioNetDriver {
     }
     
     fn set_mac_address(&mut self, mac: [u8; 6]) -> DriverResult<()> {
-        // Update internal MAC address
-        self.mac_address = mac;
-        
-        // If device supports MAC configuration, update it
-        if self.features & VIRTIO_NET_F_MAC != 0 {
-            // Write MAC address to device configuration space
-            for i in 0..6 {
-                self.mmio.write_u8(VIRTIO_MMIO_CONFIG + i, mac[i])?;
+        probe!(PROBES, NET_SET_MAC_ADDRESS, [mac], {
+            if self.features & VIRTIO_NET_F_CTRL_MAC_ADDR != 0 {
+                self.send_control_request(VIRTIO_NET_CTRL_MAC, VIRTIO_NET_CTRL_MAC_ADDR_SET, &mac)?;
+            } else if self.features & VIRTIO_NET_F_MAC != 0 {
+                // Without the command the address is written to the device
+                // configuration space, which only legacy devices honour
+                for i in 0..6 {
+                    self.mmio.write_u8(VIRTIO_MMIO_CONFIG + i, mac[i])?;
+                }
             }
-        }
-        
-        Ok(())
+
+            self.mac_address = mac;
+            self.rx_mode.mac = mac;
+            Ok(())
+        })
     }
 }
 
@@ -639,6 +828,11 @@ impl VirtioNetDriver {
             tx_queue: None,
             rx_queue_memory: None,
             tx_queue_memory: None,
+            rx_pool: None,
+            rx_filter: None,
+            multicast: MulticastFilter::default(),
+            ctrl_queue: None,
+            rx_mode: RxMode::new(mac_address),
         })
     }
     
@@ -660,6 +854,12 @@ impl VirtioNetDriver {
     }
     
     fn handle_rx_interrupt(&mut self) -> DriverResult<()> {
+        if self.rx_pool.is_some() {
+            self.deliver_rx_buffers()?;
+            self.mmio.write_u32(VIRTIO_MMIO_INTERRUPT_ACK, 0x01)?;
+            return Ok(());
+        }
+        
         // Process received packets from RX virtqueue
         let rx_queue = self.rx_queue.as_mut()
             .ok_or(DriverError::General)?;
@@ -686,13 +886,20 @@ impl VirtioNetDriver {
                 let payload = &packet_data[payload_start..];
                 
                 // Update statistics
-                self.stats.rx_packets += 1;
-                self.stats.rx_bytes += payload.len() as u64;
+                let dropped = self.rx_filter.as_mut()
+                    .is_some_and(|filter| filter.run(payload, monotonic_ns()) == Verdict::Drop);
+                if !self.multicast.accepts(payload) {
+                    // Unjoined group, not counted
+                } else if dropped {
+                    self.stats.record_rx_drop(0);
+                } else {
+                    self.stats.record_rx(0, payload.len());
+                }
                 
                 // Process packet based on flags
                 if header.flags & 0x01 != 0 {
                     // Bad packet, update error count
-                    self.stats.rx_errors += 1;
+                    self.stats.record_rx_error(ErrorKind::Other);
                 }
             }
             
@@ -706,6 +913,153 @@ impl VirtioNetDriver {
         Ok(())
     }
     
+    /// Lay out a receive pool in shared memory and register it with the
+    /// network server
+    fn attach_rx_pool(&mut self) -> DriverResult<()> {
+        if self.rx_pool.is_some() {
+            return Ok(());
+        }
+        let layout = PoolLayout::page_sized(RX_POOL_BUFFERS).map_err(|_| DriverError::InitializationFailed)?;
+        // VirtIO MMIO devices report no NUMA node: the pool goes anywhere
+        let node = None;
+        let memory = orion_sys::shm_create(layout.region_size(), shm_flags(node))
+            .map_err(|_| DriverError::MemoryError)?;
+        let (mapping, size) = orion_sys::shm_attach(memory, 0, 0).map_err(|_| DriverError::MemoryError)?;
+        
+        let watermarks = Watermarks::for_ring(RX_POOL_BUFFERS.min(self.rx_queue_size as u32));
+        let pool = unsafe { NodePool::init(mapping as *mut u8, size, layout, node, watermarks) };
+        let mut net = IpcChannel::connect("net");
+        let registered = pool.ok().and_then(|pool| {
+            let reply = net.call(&RxPoolRequest::Register { memory }.encode()).ok()?;
+            if reply.len() < 8 || reply[..4] != 0i32.to_le_bytes() {
+                return None;
+            }
+            Some((pool, u32::from_le_bytes([reply[4], reply[5], reply[6], reply[7]])))
+        });
+        match registered {
+            Some((pool, id)) => {
+                self.rx_pool = Some(RxPoolBinding {
+                    pool,
+                    id,
+                    mapping,
+                    net,
+                    posted: [0; RX_POOL_BUFFERS as usize],
+                    throttled: false,
+                });
+                Ok(())
+            }
+            None => {
+                let _ = orion_sys::shm_detach(mapping);
+                Err(DriverError::NoResources)
+            }
+        }
+    }
+    
+    /// Unregister the receive pool; the device must already be reset
+    fn detach_rx_pool(&mut self) {
+        if let Some(mut binding) = self.rx_pool.take() {
+            let _ = binding.net.call(&RxPoolRequest::Unregister { pool: binding.id }.encode());
+            let _ = orion_sys::shm_detach(binding.mapping);
+        }
+    }
+    
+    /// Post free pool buffers on the RX virtqueue once it runs below the
+    /// low watermark
+    fn refill_rx_queue(&mut self) -> DriverResult<()> {
+        let (Some(binding), Some(rx_queue)) = (self.rx_pool.as_mut(), self.rx_queue.as_mut()) else {
+            return Ok(());
+        };
+        
+        let posted_buffers = &mut binding.posted;
+        let posted = binding.pool.refill(|buffer, address| {
+            let Some(desc_id) = rx_queue.alloc_desc(1) else {
+                return false;
+            };
+            if desc_id as usize >= posted_buffers.len() {
+                rx_queue.free_desc(desc_id, 1);
+                return false;
+            }
+            unsafe {
+                let desc = rx_queue.desc.offset(desc_id as isize);
+                (*desc).addr = address as u64;
+                (*desc).len = PAGE_BUFFER_SIZE;
+                (*desc).flags = VIRTIO_DESC_F_WRITE;
+            }
+            posted_buffers[desc_id as usize] = buffer;
+            rx_queue.add_to_avail(desc_id);
+            true
+        }).map_err(|_| DriverError::General)?;
+        
+        if posted > 0 {
+            self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, 0)?; // Queue 0 for RX
+        }
+        Ok(())
+    }
+    
+    /// Pass filled pool buffers to the network server by index and repost
+    /// the buffers it recycled
+    fn deliver_rx_buffers(&mut self) -> DriverResult<()> {
+        let (Some(binding), Some(rx_queue)) = (self.rx_pool.as_mut(), self.rx_queue.as_mut()) else {
+            return Ok(());
+        };
+        
+        let header_len = core::mem::size_of::<VirtioNetHeader>();
+        while let Some((desc_id, written)) = rx_queue.next_used() {
+            let buffer = binding.posted[desc_id as usize];
+            rx_queue.free_desc(desc_id, 1);
+            
+            let written = written as usize;
+            let mut completion = RxCompletion { buffer, ..RxCompletion::default() };
+            if written < header_len || written > PAGE_BUFFER_SIZE as usize {
+                // Handed back unused
+                self.stats.record_rx_error(ErrorKind::Length);
+            } else {
+                let address = binding.pool.buffer_ptr(buffer).map_err(|_| DriverError::General)?;
+                let header = unsafe { &*(address as *const VirtioNetHeader) };
+                let frame = unsafe { core::slice::from_raw_parts(address.add(header_len), written - header_len) };
+                if !self.multicast.accepts(frame) {
+                    // Unjoined group: handed back unused without counting a drop
+                } else {
+                    match self.rx_filter.as_mut().map(|filter| filter.run(frame, monotonic_ns())) {
+                        Some(Verdict::Drop) => {
+                            // Dropped before the server sees it: the buffer goes back unused
+                            self.stats.record_rx_drop(0);
+                        }
+                        verdict => {
+                            if let Some(Verdict::Redirect(queue)) = verdict {
+                                completion.queue = queue;
+                            }
+                            completion.offset = header_len as u16;
+                            completion.length = frame.len() as u16;
+                            if header.flags & VIRTIO_NET_HDR_F_DATA_VALID != 0 {
+                                completion.flags |= FLAG_CHECKSUM_VALID;
+                            }
+                            self.stats.record_rx(completion.queue as usize, completion.length as usize);
+                        }
+                    }
+                }
+            }
+            binding.pool.complete(&completion).map_err(|_| DriverError::General)?;
+        }
+        
+        // The server has recycled every delivered buffer when it replies,
+        // unless socket memory is under pressure; frames left pending go
+        // with the next doorbell
+        if binding.pool.pool().pending() > 0 || binding.throttled {
+            let reply = binding.net.call(&RxPoolRequest::Deliver { pool: binding.id }.encode());
+            if let Some(reply) = reply.ok().and_then(|reply| DeliverReply::decode(&reply)) {
+                binding.throttled = reply.backpressure;
+            }
+        }
+        
+        // Backpressure: posting nothing lets the device run out of buffers
+        // instead of queueing frames the server cannot take
+        if binding.throttled {
+            return Ok(());
+        }
+        self.refill_rx_queue()
+    }
+    
     fn handle_tx_interrupt(&mut self) -> DriverResult<()> {
         // Process transmitted packet completions from TX virtqueue
         let tx_queue = self.tx_queue.as_mut()
@@ -723,12 +1077,11 @@ impl VirtioNetDriver {
             };
             
             // Update transmission statistics
-            self.stats.tx_packets += 1;
-            self.stats.tx_bytes += result.len() as u64;
-            
-            // Check for transmission errors
             if result.len() == 0 {
-                self.stats.tx_errors += 1;
+                // Completed without data: the device could not fetch it
+                self.stats.record_tx_error(ErrorKind::Dma);
+            } else {
+                self.stats.record_tx(0, result.len());
             }
             
             // Free descriptor for reuse
@@ -745,18 +1098,15 @@ impl VirtioNetDriver {
         // Handle configuration changes (link status, etc.)
         if self.features & VIRTIO_NET_F_STATUS != 0 {
             // Read link status from device configuration
-            let status = self.mmio.read_u16(VIRTIO_MMIO_CONFIG + 6)?;
+            let status = self.mmio.read_u16(VIRTIO_MMIO_CONFIG + VIRTIO_NET_CONFIG_STATUS)?;
             let was_link_up = self.link_up;
-            self.link_up = (status & 1) != 0;
+            self.link_up = (status & VIRTIO_NET_S_LINK_UP) != 0;
             
             // Handle link state changes
             if was_link_up != self.link_up {
                 if self.link_up {
                     // Link came up - reset statistics and reinitialize queues
-                    self.stats.rx_packets = 0;
-                    self.stats.tx_packets = 0;
-                    self.stats.rx_errors = 0;
-                    self.stats.tx_errors = 0;
+                    self.stats = NetworkStats::default();
                     
                     // Reinitialize RX/TX queues
                     self.initialize_network_queues()?;
@@ -766,8 +1116,15 @@ impl VirtioNetDriver {
                 }
             }
             
-            // Read additional configuration if available
-            if self.features & VIRTIO_NET_F_MAC != 0 {
+            // After a live migration the device asks to be announced on
+            // the new host's network
+            if status & VIRTIO_NET_S_ANNOUNCE != 0 && self.features & VIRTIO_NET_F_GUEST_ANNOUNCE != 0 {
+                self.announce()?;
+            }
+            
+            // Read additional configuration if available; an address set
+            // through the control queue does not show up there
+            if self.features & VIRTIO_NET_F_MAC != 0 && self.features & VIRTIO_NET_F_CTRL_MAC_ADDR == 0 {
                 // Update MAC address if changed
                 for i in 0..6 {
                     let mac_byte = self.mmio.read_u8(VIRTIO_MMIO_CONFIG + i)?;
@@ -830,8 +1187,7 @@ impl VirtioNetDriver {
             VIRTIO_NET_F_CSUM | VIRTIO_NET_F_GUEST_CSUM | VIRTIO_NET_F_MAC |
             VIRTIO_NET_F_GSO | VIRTIO_NET_F_GUEST_TSO4 | VIRTIO_NET_F_GUEST_TSO6 |
             VIRTIO_NET_F_HOST_TSO4 | VIRTIO_NET_F_HOST_TSO6 | VIRTIO_NET_F_STATUS |
-            VIRTIO_NET_F_CTRL_VQ | VIRTIO_NET_F_CTRL_RX | VIRTIO_NET_F_CTRL_VLAN |
-            VIRTIO_NET_F_MQ
+            VIRTIO_NET_CTRL_FEATURES | VIRTIO_NET_F_MQ
         );
         self.mmio.write_u64(VIRTIO_MMIO_DRIVER_FEATURES, driver_features)?;
         self.features = driver_features;
@@ -842,36 +1198,194 @@ impl VirtioNetDriver {
         
         // Initialize queues
         self.initialize_network_queues()?;
+        self.ctrl_queue = Self::setup_control_queue(&self.mmio, self.features)?;
         
         // Set DRIVER_OK bit
         self.mmio.write_u32(VIRTIO_MMIO_STATUS, 
             VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER | 
             VIRTIO_STATUS_FEATURES_OK | VIRTIO_STATUS_DRIVER_OK)?;
         
+        // Without a receive pool frames are copied out by receive_packet()
+        if self.attach_rx_pool().is_ok() {
+            self.refill_rx_queue()?;
+        }
+        
+        // A device that refuses keeps the receive mode it was reset to
+        let _ = self.program_rx_mode();
+        
         Ok(())
     }
     
-    /// Send control request via control virtqueue
-    fn send_control_request(&mut self, data: &[u8]) -> DriverResult<()> {
-        // Real control virtqueue implementation
-        // Use the control virtqueue (queue 2) for control operations
-        if self.features & VIRTIO_NET_F_CTRL_VQ == 0 {
-            return Err(DriverError::Unsupported);
+    /// Set up the control virtqueue, which comes after the receive and
+    /// transmit queues of every queue pair
+    fn setup_control_queue(mmio: &MmioAccessor, features: u64) -> DriverResult<Option<ControlQueue>> {
+        if features & VIRTIO_NET_F_CTRL_VQ == 0 {
+            return Ok(None);
         }
         
-        // Control virtqueue is typically queue 2
-        let _control_queue_sel = 2;
-        let control_addr = VIRTIO_MMIO_CONFIG + 0x10; // Control register offset
+        let pairs = if features & VIRTIO_NET_F_MQ != 0 {
+            mmio.read_u16(VIRTIO_MMIO_CONFIG + VIRTIO_NET_CONFIG_MAX_QUEUE_PAIRS)?.max(1)
+        } else {
+            1
+        };
+        let index = pairs * 2;
+        mmio.write_u32(VIRTIO_MMIO_QUEUE_SEL, index as u32)?;
+        let size = (mmio.read_u32(VIRTIO_MMIO_QUEUE_NUM_MAX)? as u16).min(CTRL_QUEUE_SIZE);
+        // A command takes up to three descriptors
+        if size < 3 {
+            return Err(DriverError::DeviceError);
+        }
+        
+        let memory = Self::allocate_virtqueue_memory(size)?;
+        mmio.write_u32(VIRTIO_MMIO_QUEUE_NUM, size as u32)?;
+        mmio.write_u64(0x040, memory as u64)?; // Queue address
+        mmio.write_u32(VIRTIO_MMIO_QUEUE_READY, 1)?;
         
-        // Write control data
-        for (i, &byte) in data.iter().enumerate() {
-            self.mmio.write_u8(control_addr + i, byte)?;
+        Ok(Some(ControlQueue {
+            queue: VirtioQueue::new(memory, size)?,
+            index,
+            buffer: [0; CTRL_BUFFER_SIZE],
+        }))
+    }
+    
+    /// Run one command on the control virtqueue and wait for the device
+    /// to acknowledge it
+    fn send_control_request(&mut self, class: u8, command: u8, data: &[u8]) -> DriverResult<()> {
+        let ctrl = self.ctrl_queue.as_mut().ok_or(DriverError::Unsupported)?;
+        let ack_offset = CTRL_BUFFER_SIZE - 1;
+        if 2 + data.len() > ack_offset {
+            return Err(DriverError::InvalidParameter);
         }
         
-        // Trigger control operation
-        self.mmio.write_u32(control_addr + 0x08, 0x01)?;
+        ctrl.buffer[0] = class;
+        ctrl.buffer[1] = command;
+        ctrl.buffer[2..2 + data.len()].copy_from_slice(data);
+        ctrl.buffer[ack_offset] = !VIRTIO_NET_OK;
+        
+        // Header and data are read by the device, the acknowledgement
+        // written; commands without data use two descriptors
+        let base = ctrl.buffer.as_ptr() as u64;
+        let mut segments = [(base, 2u32, 0u16); 3];
+        let mut count = 1;
+        if !data.is_empty() {
+            segments[count] = (base + 2, data.len() as u32, 0);
+            count += 1;
+        }
+        segments[count] = (base + ack_offset as u64, 1, VIRTIO_DESC_F_WRITE);
+        count += 1;
         
-        Ok(())
+        let head = ctrl.queue.alloc_desc(count as u16).ok_or(DriverError::ResourceBusy)?;
+        let mut desc_id = head;
+        for (i, &(addr, len, flags)) in segments[..count].iter().enumerate() {
+            unsafe {
+                let desc = ctrl.queue.desc.offset(desc_id as isize);
+                (*desc).addr = addr;
+                (*desc).len = len;
+                (*desc).flags = if i + 1 < count { flags | VIRTIO_DESC_F_NEXT } else { flags };
+                desc_id = (*desc).next;
+            }
+        }
+        ctrl.queue.add_to_avail(head);
+        self.mmio.write_u32(VIRTIO_MMIO_QUEUE_NOTIFY, ctrl.index as u32)?;
+        
+        let deadline = monotonic_ns() + CTRL_TIMEOUT_NS;
+        loop {
+            match ctrl.queue.next_used() {
+                Some((id, _)) if id == head => break,
+                Some(_) => {}
+                // The descriptors stay with the device, which may still use them
+                None if monotonic_ns() >= deadline => return Err(DriverError::Timeout),
+                None => core::hint::spin_loop(),
+            }
+        }
+        ctrl.queue.free_desc(head, count as u16);
+        
+        let ack = unsafe { core::ptr::read_volatile(&ctrl.buffer[ack_offset]) };
+        if ack == VIRTIO_NET_OK {
+            Ok(())
+        } else {
+            Err(DriverError::DeviceError)
+        }
+    }
+    
+    /// Turn promiscuous or all-multicast reception on or off
+    fn set_rx_flag(&mut self, command: u8, enabled: bool) -> DriverResult<()> {
+        if self.features & VIRTIO_NET_F_CTRL_RX == 0 {
+            return Err(DriverError::Unsupported);
+        }
+        self.send_control_request(VIRTIO_NET_CTRL_RX, command, &[enabled as u8])
+    }
+    
+    /// Load the multicast list into the device MAC filter table, or accept
+    /// every group when the list is too long or all-multicast was asked
+    /// for. The unicast half of the table stays empty: the station
+    /// address always passes.
+    fn program_multicast(&mut self) -> DriverResult<()> {
+        if self.features & VIRTIO_NET_F_CTRL_RX == 0 {
+            // The device passes every group and the receive path sorts them
+            return Ok(());
+        }
+        let all = self.rx_mode.all_multicast || self.multicast.accepts_all();
+        self.set_rx_flag(VIRTIO_NET_CTRL_RX_ALLMULTI, all)?;
+        if all {
+            return Ok(());
+        }
+        
+        let addresses = self.multicast.addresses();
+        let mut table = [0u8; 8 + 6 * MAX_MULTICAST_ADDRESSES];
+        table[4..8].copy_from_slice(&(addresses.len() as u32).to_le_bytes());
+        for (i, address) in addresses.iter().enumerate() {
+            table[8 + i * 6..14 + i * 6].copy_from_slice(address);
+        }
+        let length = 8 + addresses.len() * 6;
+        self.send_control_request(VIRTIO_NET_CTRL_MAC, VIRTIO_NET_CTRL_MAC_TABLE_SET, &table[..length])
+    }
+    
+    /// Bring the device in line with the recorded receive mode; devices
+    /// come out of reset promiscuous
+    fn program_rx_mode(&mut self) -> DriverResult<()> {
+        if self.features & VIRTIO_NET_F_CTRL_RX == 0 {
+            return Ok(());
+        }
+        self.set_rx_flag(VIRTIO_NET_CTRL_RX_PROMISC, self.rx_mode.promiscuous)?;
+        self.program_multicast()
+    }
+    
+    /// Make one receive mode change on the device (see orion_pktfilter::rx_mode)
+    fn apply_rx_mode(&mut self, change: &RxModeChange) -> DriverResult<()> {
+        match *change {
+            RxModeChange::Flags { promiscuous, all_multicast } => {
+                self.set_rx_flag(VIRTIO_NET_CTRL_RX_PROMISC, promiscuous)?;
+                let all = all_multicast || self.multicast.accepts_all();
+                self.set_rx_flag(VIRTIO_NET_CTRL_RX_ALLMULTI, all)
+            }
+            RxModeChange::Mac(mac) => self.set_mac_address(mac),
+            RxModeChange::AddVlan(vid) | RxModeChange::RemoveVlan(vid) => {
+                if self.features & VIRTIO_NET_F_CTRL_VLAN == 0 {
+                    return Err(DriverError::Unsupported);
+                }
+                let command = match change {
+                    RxModeChange::AddVlan(_) => VIRTIO_NET_CTRL_VLAN_ADD,
+                    _ => VIRTIO_NET_CTRL_VLAN_DEL,
+                };
+                self.send_control_request(VIRTIO_NET_CTRL_VLAN, command, &vid.to_le_bytes())
+            }
+        }
+    }
+    
+    /// Send gratuitous ARP for the station's addresses on the network the
+    /// device now sits on, then acknowledge, which clears
+    /// VIRTIO_NET_S_ANNOUNCE
+    fn announce(&mut self) -> DriverResult<()> {
+        if self.link_up {
+            for announcement in self.rx_mode.announce_frames() {
+                let mut frame = [0u8; MIN_FRAME_SIZE];
+                frame[..announcement.len()].copy_from_slice(&announcement);
+                // Switches relearn the address from the next frame anyway
+                let _ = self.send_packet(&frame);
+            }
+        }
+        self.send_control_request(VIRTIO_NET_CTRL_ANNOUNCE, VIRTIO_NET_CTRL_ANNOUNCE_ACK, &[])
     }
 
     /// Allocate memory for virtqueue structures
@@ -992,6 +1506,43 @@ pub extern "C" fn driver_main() {
                             // Send packet
                             Ok(io_msg.length as usize)
                         }
+                        IoRequestType::Ioctl if io_msg.length == FILTER_IOCTL_CONTROL => {
+                            // Filter requests answer with data rather than a length
+                            let driver = match VirtioNetDriver::get_instance(0) {
+                                Ok(drv) => drv,
+                                Err(e) => return ipc.send_io_response(io_msg.header.sequence, Err(e)),
+                            };
+                            let queues = if driver.rx_pool.is_some() { RX_FILTER_QUEUES } else { 1 };
+                            let reply = handle_control(&mut driver.rx_filter, &io_msg.data, queues);
+                            return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                        }
+                        IoRequestType::Ioctl if io_msg.length == FILTER_IOCTL_MULTICAST => {
+                            let driver = match VirtioNetDriver::get_instance(0) {
+                                Ok(drv) => drv,
+                                Err(e) => return ipc.send_io_response(io_msg.header.sequence, Err(e)),
+                            };
+                            let reply = handle_multicast(&mut driver.multicast, &io_msg.data);
+                            // The receive path keeps checking groups should the table not load
+                            let _ = driver.program_multicast();
+                            return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                        }
+                        IoRequestType::Ioctl if io_msg.length == FILTER_IOCTL_RX_MODE => {
+                            let driver = match VirtioNetDriver::get_instance(0) {
+                                Ok(drv) => drv,
+                                Err(e) => return ipc.send_io_response(io_msg.header.sequence, Err(e)),
+                            };
+                            // Changes are made on the device first and only then recorded
+                            let mut mode = driver.rx_mode.clone();
+                            let reply = handle_rx_mode(&mut mode, &io_msg.data, |change| {
+                                driver.apply_rx_mode(change).map_err(rx_mode_status)
+                            });
+                            driver.rx_mode = mode;
+                            return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                        }
+                        IoRequestType::Ioctl if io_msg.length == PROBE_IOCTL_CONTROL => {
+                            let reply = orion_probe::handle_control(&PROBES, &io_msg.data);
+                            return ipc.send_response(io_msg.header.sequence, 0, &reply);
+                        }
                         IoRequestType::Ioctl => {
                             // Handle network configuration
                             Ok(0)
@@ -38,6 +38,7 @@
      PowerState, DeviceState, HotplugEvent,
  };
  use orion_async::{Future, Pin, Poll, Context, Waker, AsyncMutex, AsyncChannel};
+ use orion_ipc::IpcChannel;
  use alloc::{vec::Vec, collections::BTreeMap, boxed::Box, string::String};
  use core::{alloc::{GlobalAlloc, Layout}, mem, slice, ptr};
  use core::future::Future as CoreFuture;
@@ -118,6 +119,9 @@
  const HID_USAGE_GAMEPAD: u16 = 0x05;
  const HID_USAGE_MULTI_TOUCH: u16 = 0x22;
  
+ // Keymap server request (see services/keymap/src/protocol.rs)
+ const KEYMAP_OP_KEY: u32 = 1;
+ 
  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
  pub enum HidDeviceType {
      Unknown,
@@ -279,6 +283,7 @@
      
      // Event Processing
      event_queue: AsyncChannel<HidEvent>,
+     keymap_channel: IpcChannel,
      event_processor: Option<Pin<Box<dyn Future<Output = ()>>>>,
      
      // Input State Tracking
@@ -328,6 +333,7 @@
              device_state: DeviceState::Disconnected,
              power_state: PowerState::Active,
              event_queue: AsyncChannel::new(1024),
+             keymap_channel: IpcChannel::connect("keymap"),
              event_processor: None,
              previous_states: BTreeMap::new(),
              keyboard_state: None,
@@ -477,6 +483,7 @@
                  self.event_queue.send(hid_event).await.map_err(|_| DriverError::IoError)?;
              }
  
+             self.forward_to_keymap(&report);
              self.keyboard_state = Some(report);
          }
          Ok(())
@@ -689,6 +696,39 @@
          events
      }
  
+     /// Report key transitions to the keymap server as HID usages
+     fn forward_to_keymap(&mut self, report: &BootKeyboardReport) {
+         let previous = self.keyboard_state.unwrap_or(BootKeyboardReport { modifiers: 0, reserved: 0, keycodes: [0; 6] });
+         let mut transitions = Vec::new();
+ 
+         // Modifier bits are usages 0xE0-0xE7
+         for bit in 0..8u32 {
+             let mask = 1u8 << bit;
+             if (report.modifiers ^ previous.modifiers) & mask != 0 {
+                 transitions.push((0xE0 + bit, report.modifiers & mask != 0));
+             }
+         }
+         // Usages 0x01-0x03 are rollover and error codes, not keys
+         for &usage in previous.keycodes.iter().filter(|&&usage| usage > 0x03) {
+             if !report.keycodes.contains(&usage) {
+                 transitions.push((usage as u32, false));
+             }
+         }
+         for &usage in report.keycodes.iter().filter(|&&usage| usage > 0x03) {
+             if !previous.keycodes.contains(&usage) {
+                 transitions.push((usage as u32, true));
+             }
+         }
+ 
+         for (usage, down) in transitions {
+             let mut message = [0u8; 12];
+             message[0..4].copy_from_slice(&KEYMAP_OP_KEY.to_le_bytes());
+             message[4..8].copy_from_slice(&usage.to_le_bytes());
+             message[8..12].copy_from_slice(&(down as u32).to_le_bytes());
+             let _ = self.keymap_channel.post(&message);
+         }
+     }
+ 
      // ========================================
      // POWER MANAGEMENT
      // ========================================
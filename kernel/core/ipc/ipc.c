@@ -15,6 +15,8 @@
 #include <orion/types.h>
 #include <orion/mm.h>
 #include <orion/structures.h
+#include <orion/sched_rt.h>
+#include <orion/ipc_stats.h>
 
 // Capability constants (if not defined elsewhere)
 #ifndef CAP_READ
@@ -31,6 +33,7 @@
 
 // Forward declarations
 extern or_cap_t cap_create(uint32_t type, uint64_t object_id, uint64_t rights, uint64_t owner_pid);
+extern bool security_check_ipc_allowed(uint64_t pid, uint64_t peer_pid);
 
 // ========================================
 // CONSTANTS AND CONFIGURATION
@@ -42,6 +45,11 @@ extern or_cap_t cap_create(uint32_t type, uint64_t object_id, uint64_t rights, u
 #define IPC_SHARED_POOL_SIZE (16 * 1024 * 1024) // 16MB shared pool
 #define IPC_RING_BUFFER_SIZE 4096
 
+// Synchronous calls: largest request or reply, and the part carried in
+// the call itself rather than a shared page
+#define IPC_CALL_MAX_SIZE PAGE_SIZE
+#define IPC_CALL_INLINE_SIZE 256
+
 // Flags for messages
 #define IPC_MSG_FLAG_ZERO_COPY 0x00000001
 #define IPC_MSG_FLAG_URGENT 0x00000002
@@ -73,7 +81,7 @@ typedef struct ipc_msg_slot
     uint64_t offset;              // Offset within the page
     or_cap_t transferred_caps[8]; // Transferred capabilities
     uint32_t cap_count;           // Number of capabilities
-    uint32_t reserved;            // Padding
+    uint32_t sender_prio;         // Sender's real-time priority, inherited by the receiver
     uint8_t data[256];            // Inline data storage for small messages
 } ipc_msg_slot_t;
 
@@ -87,6 +95,24 @@ typedef struct ipc_msg_queue
     char cache_line_padding[64]; // Avoid false sharing
 } ipc_msg_queue_t;
 
+// Synchronous call, on the caller's kernel stack until it is answered.
+// The request is replaced by the reply in the same buffer
+typedef struct ipc_call
+{
+    thread_t *caller;
+    thread_t *server;     // Thread serving it, NULL while queued
+    uint32_t size;        // Request, then reply size
+    int32_t status;       // Error failing the call, OR_OK otherwise
+    uint32_t sender_prio; // Caller's real-time priority
+    bool handoff;         // Caller switched straight to the server and waits blocked
+    volatile bool done;   // Reply written
+    uint64_t page_phys;   // Shared page beyond IPC_CALL_INLINE_SIZE
+    uint64_t queued_at;   // Made, for the time it waits for a server
+    uint64_t started_at;  // Taken by the server, for the time it is served
+    uint8_t data[IPC_CALL_INLINE_SIZE];
+    struct ipc_call *next;
+} ipc_call_t;
+
 // Complete IPC port
 typedef struct ipc_port
 {
@@ -104,12 +130,31 @@ typedef struct ipc_port
     thread_t *waiting_senders;   // Threads blocked on send
     thread_t *waiting_receivers; // Threads blocked on receive
     spinlock_t waiters_lock;     // Lock for waiting lists
+    thread_t *server_thread;     // Thread serving the last request received
+
+    // Synchronous calls, under waiters_lock
+    ipc_call_t *calls;            // Calls waiting for a server, oldest first
+    ipc_call_t *calls_in_service; // Calls taken by a server, not yet answered
+    thread_t *call_server;        // Server parked in ipc_reply_recv
 
     // Statistics
     atomic64_t msgs_sent;         // Messages sent
     atomic64_t msgs_received;     // Messages received
     atomic64_t bytes_transferred; // Bytes transferred
 
+    // Endpoint statistics (see ipc_stats.h)
+    atomic64_t queued;           // Messages and calls waiting for the server
+    atomic64_t queued_max;       // Most waiting at once
+    atomic64_t msgs_dropped;     // Never served
+    atomic64_t serving_since;    // Last message received, until the next receive
+    atomic64_t wait_ns_total;    // Time spent queued
+    atomic64_t wait_ns_max;
+    atomic64_t wait_buckets[IPC_STATS_BUCKETS];
+    atomic64_t handled;          // Requests the server finished with
+    atomic64_t handler_ns_total; // Time the server spent on them
+    atomic64_t handler_ns_max;
+    atomic64_t handler_buckets[IPC_STATS_BUCKETS];
+
     // Configuration
     uint32_t max_queue_size; // Maximum queue size
     uint32_t max_msg_size;   // Maximum message size
@@ -190,6 +235,67 @@ static uint64_t get_next_sequence(atomic64_t *sequence)
     return atomic_fetch_add(sequence, 1);
 }
 
+// ========================================
+// ENDPOINT STATISTICS
+// ========================================
+
+static void ipc_stats_raise(atomic64_t *max, uint64_t value)
+{
+    uint64_t seen = atomic_load(max);
+    while (value > seen && !cas_sequence(max, seen, value))
+    {
+        seen = atomic_load(max);
+    }
+}
+
+// Count a duration in its total, maximum and histogram bucket
+static void ipc_stats_latency(atomic64_t *total, atomic64_t *max, atomic64_t *buckets, uint64_t ns)
+{
+    uint32_t bucket = 0;
+    for (uint64_t bound = IPC_STATS_FIRST_BOUND_NS; bucket < IPC_STATS_BUCKETS - 1 && ns > bound; bound *= 10)
+    {
+        bucket++;
+    }
+    atomic_fetch_add(total, ns);
+    ipc_stats_raise(max, ns);
+    atomic_fetch_add(&buckets[bucket], 1);
+}
+
+// A message or call starts waiting for the server
+static void ipc_stats_queued(ipc_port_t *port)
+{
+    ipc_stats_raise(&port->queued_max, atomic_fetch_add(&port->queued, 1) + 1);
+}
+
+// The server takes a request made at `queued_at`; `was_queued` if it
+// waited in the queue rather than being handed over
+static void ipc_stats_taken(ipc_port_t *port, uint64_t queued_at, bool was_queued, uint64_t now)
+{
+    if (was_queued)
+    {
+        atomic_fetch_sub(&port->queued, 1);
+    }
+    ipc_stats_latency(&port->wait_ns_total, &port->wait_ns_max, port->wait_buckets, now - queued_at);
+}
+
+// The server is done with a request it took at `started_at`
+static void ipc_stats_handled(ipc_port_t *port, uint64_t started_at)
+{
+    atomic_fetch_add(&port->handled, 1);
+    ipc_stats_latency(&port->handler_ns_total, &port->handler_ns_max, port->handler_buckets,
+                      arch_get_timestamp() - started_at);
+}
+
+// A message or call will never be served
+static void ipc_stats_dropped(ipc_port_t *port, bool was_queued)
+{
+    if (was_queued)
+    {
+        atomic_fetch_sub(&port->queued, 1);
+    }
+    atomic_fetch_add(&port->msgs_dropped, 1);
+}
+
 // ========================================
 // SHARED MEMORY POOL MANAGEMENT
 // ========================================
@@ -330,6 +436,7 @@ static bool ipc_queue_send(ipc_msg_queue_t *queue, ipc_msg_slot_t *msg)
                 slot->page_phys = msg->page_phys;
                 slot->offset = msg->offset;
                 slot->cap_count = msg->cap_count;
+                slot->sender_prio = msg->sender_prio;
 
                 for (uint32_t i = 0; i < msg->cap_count && i < 8; i++)
                 {
@@ -354,6 +461,11 @@ static bool ipc_queue_send(ipc_msg_queue_t *queue, ipc_msg_slot_t *msg)
     }
 }
 
+static bool ipc_queue_empty(ipc_msg_queue_t *queue)
+{
+    return atomic_load(&queue->head) == atomic_load(&queue->tail);
+}
+
 static bool ipc_queue_recv(ipc_msg_queue_t *queue, ipc_msg_slot_t *msg)
 {
     uint64_t tail = atomic_load(&queue->tail);
@@ -617,6 +729,12 @@ int ipc_send_message(or_cap_t port_cap, const void *data, size_t size, uint64_t
         return -OR_EPERM;
     }
 
+    // Sandboxed processes may only talk to the endpoints in their profile
+    if (!security_check_ipc_allowed(current->pid, port->owner_pid))
+    {
+        return -OR_EACCES;
+    }
+
     kdebug("IPC send: port=%llu, size=%llu",
            (unsigned long long)port_cap, (unsigned long long)size);
 
@@ -666,6 +784,11 @@ int ipc_send_message(or_cap_t port_cap, const void *data, size_t size, uint64_t
     msg.timestamp = arch_get_timestamp();
     msg.cap_count = 0;
 
+    // Priority inheritance: whoever serves the request runs at least at
+    // the sender's real-time priority
+    thread_t *current_thread = scheduler_get_current_thread();
+    msg.sender_prio = sched_rt_prio(current_thread);
+
     // Allocate memory based on size
     if (msg.flags & IPC_MSG_FLAG_ZERO_COPY)
     {
@@ -715,6 +838,7 @@ int ipc_send_message(or_cap_t port_cap, const void *data, size_t size, uint64_t
     // Try to send the message
     uint64_t start_time = arch_get_timestamp();
     bool sent = false;
+    bool blocked = false;
 
     while (!sent)
     {
@@ -722,17 +846,29 @@ int ipc_send_message(or_cap_t port_cap, const void *data, size_t size, uint64_t
 
         if (!sent)
         {
-            // Queue full, check timeout
+            // Queue full: the server works for us until there is room
+            if (!blocked && port->server_thread)
+            {
+                sched_rt_block_on(current_thread, port->server_thread);
+                blocked = true;
+            }
+
+            // Check timeout
             if (timeout_ns > 0)
             {
                 uint64_t elapsed = arch_get_timestamp() - start_time;
                 if (elapsed >= timeout_ns)
                 {
                     // Timeout, release resources
+                    if (blocked)
+                    {
+                        sched_rt_block_on(current_thread, NULL);
+                    }
                     if (msg.page_phys)
                     {
                         ipc_shared_free_page(&g_ipc_registry->shared_pool, msg.page_phys);
                     }
+                    ipc_stats_dropped(port, false);
                     return -OR_ETIMEDOUT;
                 }
             }
@@ -741,17 +877,30 @@ int ipc_send_message(or_cap_t port_cap, const void *data, size_t size, uint64_t
             scheduler_sleep_ns(1000); // 1µs
         }
     }
+    if (blocked)
+    {
+        sched_rt_block_on(current_thread, NULL);
+    }
+    ipc_stats_queued(port);
 
     // Wake up waiting threads for reception
     spinlock_lock(&port->waiters_lock);
-    if (port->waiting_receivers)
+    thread_t *receiver = port->waiting_receivers;
+    if (receiver)
     {
-        thread_t *thread = port->waiting_receivers;
-        port->waiting_receivers = thread->next;
-        scheduler_wakeup_process(thread->parent_process);
+        port->waiting_receivers = receiver->next;
     }
+    thread_t *server = receiver ? receiver : port->server_thread;
     spinlock_unlock(&port->waiters_lock);
 
+    // Boost whoever will serve the request, before it is queued: the
+    // receiver woken, or the server still busy with an earlier request
+    sched_rt_inherit(server, msg.sender_prio);
+    if (receiver)
+    {
+        scheduler_wakeup_process(receiver->parent_process);
+    }
+
     // Update statistics
     atomic_fetch_add(&port->msgs_sent, 1);
     atomic_fetch_add(&port->bytes_transferred, size);
@@ -786,10 +935,18 @@ int ipc_recv_message(or_cap_t port_cap, void *buffer, size_t buffer_size, uint64
     kdebug("IPC recv: port=%llu, buffer_size=%llu",
            (unsigned long long)port_cap, (unsigned long long)buffer_size);
 
+    // Receiving again: the server is done with the last message
+    uint64_t serving_since = atomic_load(&port->serving_since);
+    if (serving_since && cas_sequence(&port->serving_since, serving_since, 0))
+    {
+        ipc_stats_handled(port, serving_since);
+    }
+
     // Try to receive a message
     ipc_msg_slot_t msg;
     uint64_t start_time = arch_get_timestamp();
     bool received = false;
+    thread_t *server = scheduler_get_current_thread();
 
     while (!received)
     {
@@ -797,6 +954,9 @@ int ipc_recv_message(or_cap_t port_cap, void *buffer, size_t buffer_size, uint64
 
         if (!received)
         {
+            // Nothing left to serve: the previous request is answered
+            sched_rt_inherit_reset(server, 0);
+
             // No message, check timeout
             if (timeout_ns > 0)
             {
@@ -822,6 +982,21 @@ int ipc_recv_message(or_cap_t port_cap, void *buffer, size_t buffer_size, uint64
         }
     }
 
+    uint64_t received_at = arch_get_timestamp();
+    ipc_stats_taken(port, msg.timestamp, true, received_at);
+
+    // Serve the request at its sender's priority. Requests still queued
+    // keep the priority their senders donated
+    port->server_thread = server;
+    if (ipc_queue_empty(port->recv_queue))
+    {
+        sched_rt_inherit_reset(server, msg.sender_prio);
+    }
+    else
+    {
+        sched_rt_inherit(server, msg.sender_prio);
+    }
+
     // Check if buffer is large enough
     if (buffer_size < msg.data_size)
     {
@@ -830,6 +1005,7 @@ int ipc_recv_message(or_cap_t port_cap, void *buffer, size_t buffer_size, uint64
         {
             ipc_shared_free_page(&g_ipc_registry->shared_pool, msg.page_phys);
         }
+        ipc_stats_dropped(port, false);
         return -OR_EINVAL;
     }
 
@@ -865,11 +1041,335 @@ int ipc_recv_message(or_cap_t port_cap, void *buffer, size_t buffer_size, uint64
 
     // Update statistics
     atomic_fetch_add(&port->msgs_received, 1);
+    atomic_store(&port->serving_since, received_at);
 
     kdebug("IPC message received: %u bytes", msg.data_size);
     return (int)msg.data_size;
 }
 
+// ========================================
+// SYNCHRONOUS CALLS
+// ========================================
+
+// Store a request or reply in a call: inline, or in a shared page beyond
+// IPC_CALL_INLINE_SIZE
+static int ipc_call_store(ipc_call_t *call, const void *data, size_t size)
+{
+    if (size > IPC_CALL_MAX_SIZE)
+    {
+        return -OR_EINVAL;
+    }
+    if (size > IPC_CALL_INLINE_SIZE && !call->page_phys)
+    {
+        call->page_phys = ipc_shared_alloc_page(&g_ipc_registry->shared_pool);
+        if (!call->page_phys)
+        {
+            return -OR_ENOMEM;
+        }
+    }
+    if (size > 0)
+    {
+        // Identity mapping
+        memcpy(size > IPC_CALL_INLINE_SIZE ? (void *)call->page_phys : call->data, data, size);
+    }
+    call->size = (uint32_t)size;
+    return OR_OK;
+}
+
+static const void *ipc_call_payload(const ipc_call_t *call)
+{
+    return call->size > IPC_CALL_INLINE_SIZE ? (const void *)call->page_phys : call->data;
+}
+
+static void ipc_call_unlink(ipc_call_t **list, ipc_call_t *call)
+{
+    for (ipc_call_t **link = list; *link; link = &(*link)->next)
+    {
+        if (*link == call)
+        {
+            *link = call->next;
+            call->next = NULL;
+            return;
+        }
+    }
+}
+
+// Give a call to `server`, from the queue if `was_queued`. Called with
+// waiters_lock held
+static void ipc_call_start(ipc_port_t *port, ipc_call_t *call, thread_t *server, bool was_queued)
+{
+    call->started_at = arch_get_timestamp();
+    ipc_stats_taken(port, call->queued_at, was_queued, call->started_at);
+    call->server = server;
+    call->next = port->calls_in_service;
+    port->calls_in_service = call;
+    port->server_thread = server;
+}
+
+// Call taken by `server` and not answered yet. Called with waiters_lock held
+static ipc_call_t *ipc_call_served_by(ipc_port_t *port, thread_t *server)
+{
+    for (ipc_call_t *call = port->calls_in_service; call; call = call->next)
+    {
+        if (call->server == server)
+        {
+            return call;
+        }
+    }
+    return NULL;
+}
+
+// Answer a call with the reply stored in it, or fail it with `status`.
+// Once done is set the caller may return and its call vanish, so how it
+// waits is read first: `handoff` is set if it waits blocked for the
+// server to switch back to it. Returns the caller
+static thread_t *ipc_call_finish(ipc_port_t *port, ipc_call_t *call, int status, bool *handoff)
+{
+    spinlock_lock(&port->waiters_lock);
+    ipc_call_unlink(&port->calls_in_service, call);
+    thread_t *caller = call->caller;
+    *handoff = call->handoff;
+    if (status != OR_OK)
+    {
+        call->status = status;
+    }
+    call->done = true;
+    spinlock_unlock(&port->waiters_lock);
+
+    return caller;
+}
+
+// Synchronous call: send `request` to the server of `port` and wait for
+// its reply. When the server is parked in ipc_reply_recv the caller
+// switches straight to it and donates its scheduling context, and the
+// reply switches straight back, so a round trip never goes through a
+// runqueue. Otherwise the call queues for the server's next
+// ipc_reply_recv. The timeout only covers the time spent queued: a call
+// taken by the server is waited for. Returns the reply size
+int ipc_call(or_cap_t port_cap, const void *request, size_t request_size, void *reply, size_t reply_size,
+             uint64_t timeout_ns)
+{
+    if (!ipc_initialized || !g_ipc_registry || (!request && request_size) || request_size > IPC_CALL_MAX_SIZE)
+    {
+        return -OR_EINVAL;
+    }
+
+    ipc_port_t *port = ipc_find_port(port_cap);
+    if (!port)
+    {
+        return -OR_ENOENT;
+    }
+
+    process_t *current = scheduler_get_current_process();
+    thread_t *caller = scheduler_get_current_thread();
+    if (!current || !caller)
+    {
+        return -OR_EPERM;
+    }
+    if (!security_check_ipc_allowed(current->pid, port->owner_pid))
+    {
+        return -OR_EACCES;
+    }
+
+    ipc_call_t call = {0};
+    call.caller = caller;
+    call.sender_prio = sched_rt_prio(caller);
+    int result = ipc_call_store(&call, request, request_size);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    uint64_t start_time = arch_get_timestamp();
+    call.queued_at = start_time;
+    spinlock_lock(&port->waiters_lock);
+    thread_t *server = port->call_server;
+    if (server)
+    {
+        port->call_server = NULL;
+        ipc_call_start(port, &call, server, false);
+        call.handoff = true;
+    }
+    else
+    {
+        ipc_call_t **link = &port->calls;
+        while (*link)
+        {
+            link = &(*link)->next;
+        }
+        *link = &call;
+        ipc_stats_queued(port);
+    }
+    thread_t *serving = port->server_thread;
+    spinlock_unlock(&port->waiters_lock);
+
+    if (server)
+    {
+        // Fast path: the server runs the request on our time
+        sched_rt_inherit(server, call.sender_prio);
+        scheduler_handoff(server, true);
+    }
+    else
+    {
+        // Whoever serves the port works for us until the call is taken
+        sched_rt_block_on(caller, serving);
+    }
+
+    while (!call.done)
+    {
+        spinlock_lock(&port->waiters_lock);
+        // Awake before the reply: it must only wake us from now on
+        call.handoff = false;
+        if (!call.done && !call.server && timeout_ns > 0 && arch_get_timestamp() - start_time >= timeout_ns)
+        {
+            ipc_call_unlink(&port->calls, &call);
+            ipc_stats_dropped(port, true);
+            spinlock_unlock(&port->waiters_lock);
+            sched_rt_block_on(caller, NULL);
+            if (call.page_phys)
+            {
+                ipc_shared_free_page(&g_ipc_registry->shared_pool, call.page_phys);
+            }
+            return -OR_ETIMEDOUT;
+        }
+        spinlock_unlock(&port->waiters_lock);
+
+        if (!call.done)
+        {
+            scheduler_sleep_ns(1000); // 1µs
+        }
+    }
+    if (!server)
+    {
+        sched_rt_block_on(caller, NULL);
+    }
+
+    result = call.status;
+    if (result == OR_OK && call.size > reply_size)
+    {
+        result = -OR_EINVAL;
+    }
+    if (result == OR_OK && call.size > 0)
+    {
+        memcpy(reply, ipc_call_payload(&call), call.size);
+    }
+    if (call.page_phys)
+    {
+        ipc_shared_free_page(&g_ipc_registry->shared_pool, call.page_phys);
+    }
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    atomic_fetch_add(&port->msgs_sent, 1);
+    atomic_fetch_add(&port->bytes_transferred, request_size + call.size);
+    atomic_fetch_add(&g_ipc_registry->total_msgs_sent, 1);
+    return (int)call.size;
+}
+
+// Server side of ipc_call: answer the call the current thread is serving
+// with `reply`, if any, and wait for the next call on `port`. With no
+// call queued the server parks, and a caller that answered with a
+// handoff gets the CPU back straight away. Returns the request size
+int ipc_reply_recv(or_cap_t port_cap, const void *reply, size_t reply_size, void *request, size_t request_size)
+{
+    if (!ipc_initialized || !g_ipc_registry || (!reply && reply_size) || !request)
+    {
+        return -OR_EINVAL;
+    }
+
+    ipc_port_t *port = ipc_find_port(port_cap);
+    if (!port)
+    {
+        return -OR_ENOENT;
+    }
+
+    process_t *current = scheduler_get_current_process();
+    thread_t *server = scheduler_get_current_thread();
+    if (!current || !server || current->pid != port->owner_pid)
+    {
+        return -OR_EPERM;
+    }
+
+    // Answer the call being served
+    spinlock_lock(&port->waiters_lock);
+    ipc_call_t *answered = ipc_call_served_by(port, server);
+    if (answered)
+    {
+        ipc_call_unlink(&port->calls_in_service, answered);
+    }
+    spinlock_unlock(&port->waiters_lock);
+
+    thread_t *caller = NULL;
+    bool handoff = false;
+    if (answered)
+    {
+        // Off the in-service list, the call cannot be failed under us
+        int status = ipc_call_store(answered, reply, reply_size);
+        ipc_stats_handled(port, answered->started_at);
+        caller = ipc_call_finish(port, answered, status, &handoff);
+        atomic_fetch_add(&port->msgs_received, 1);
+    }
+
+    for (;;)
+    {
+        // Take the next call, or park until a caller brings one
+        spinlock_lock(&port->waiters_lock);
+        ipc_call_t *call = ipc_call_served_by(port, server);
+        if (!call && port->calls)
+        {
+            call = port->calls;
+            port->calls = call->next;
+            ipc_call_start(port, call, server, true);
+        }
+        bool closing = atomic_load(&port->state) != IPC_PORT_STATE_ACTIVE;
+        if (!call && !closing)
+        {
+            port->call_server = server;
+            server->state = THREAD_STATE_BLOCKED;
+        }
+        spinlock_unlock(&port->waiters_lock);
+
+        if (call)
+        {
+            // More work: the caller answered is only woken
+            scheduler_wakeup_thread(caller);
+            sched_rt_inherit_reset(server, call->sender_prio);
+
+            if (call->size > request_size)
+            {
+                bool ignored;
+                ipc_stats_dropped(port, false);
+                scheduler_wakeup_thread(ipc_call_finish(port, call, -OR_EINVAL, &ignored));
+                return -OR_EINVAL;
+            }
+            memcpy(request, ipc_call_payload(call), call->size);
+            return (int)call->size;
+        }
+
+        // Nothing left to serve
+        sched_rt_inherit_reset(server, 0);
+        if (closing)
+        {
+            scheduler_wakeup_thread(caller);
+            return -OR_ENOENT;
+        }
+
+        if (caller && handoff)
+        {
+            // Fast path: straight back to the caller, on its own time
+            scheduler_handoff(caller, true);
+        }
+        else
+        {
+            scheduler_wakeup_thread(caller);
+            sched_yield();
+        }
+        caller = NULL;
+    }
+}
+
 // Destroy an IPC port
 void ipc_port_destroy(or_cap_t port_cap)
 {
@@ -908,8 +1408,40 @@ void ipc_port_destroy(or_cap_t port_cap)
 
     port->waiting_senders = NULL;
     port->waiting_receivers = NULL;
+    thread_t *server = port->server_thread;
+    port->server_thread = NULL;
+    thread_t *call_server = port->call_server;
+    port->call_server = NULL;
     spinlock_unlock(&port->waiters_lock);
 
+    // The server no longer works for anyone through this port
+    sched_rt_inherit_reset(server, 0);
+    scheduler_wakeup_thread(call_server);
+
+    // Fail every call, queued or in service
+    for (;;)
+    {
+        spinlock_lock(&port->waiters_lock);
+        ipc_call_t *call = port->calls;
+        bool queued = call != NULL;
+        if (call)
+        {
+            port->calls = call->next;
+        }
+        else
+        {
+            call = port->calls_in_service;
+        }
+        spinlock_unlock(&port->waiters_lock);
+        if (!call)
+        {
+            break;
+        }
+        ipc_stats_dropped(port, queued);
+        bool ignored;
+        scheduler_wakeup_thread(ipc_call_finish(port, call, -OR_ENOENT, &ignored));
+    }
+
     // Free queues
     if (port->send_queue)
     {
@@ -933,3 +1465,63 @@ void ipc_port_destroy(or_cap_t port_cap)
 
     kdebug("Destroyed IPC port %llu", (unsigned long long)port_cap);
 }
+
+// Destroy the ports `owner_pid` still has when it goes away: the calls
+// queued on them or in service fail instead of waiting for a server that
+// is gone, which is what clients of a driver that is unloaded see
+void ipc_release_ports(uint64_t owner_pid)
+{
+    if (!ipc_initialized || !g_ipc_registry || owner_pid == 0)
+    {
+        return;
+    }
+
+    for (uint32_t i = 0; i < MAX_IPC_PORTS; i++)
+    {
+        ipc_port_t *port = &g_ipc_registry->ports[i];
+        if (atomic_load(&port->state) == IPC_PORT_STATE_ACTIVE && port->owner_pid == owner_pid)
+        {
+            ipc_port_destroy(port->cap_id);
+        }
+    }
+}
+
+// Statistics of the first active port at or after `cursor`, only among
+// those of `owner_pid` unless it is 0. Returns the next cursor
+int64_t ipc_get_port_stats(uint64_t cursor, uint64_t owner_pid, ipc_port_stats_t *stats)
+{
+    if (!ipc_initialized || !g_ipc_registry || !stats)
+    {
+        return -OR_EINVAL;
+    }
+
+    for (uint64_t i = cursor; i < MAX_IPC_PORTS; i++)
+    {
+        ipc_port_t *port = &g_ipc_registry->ports[i];
+        if (atomic_load(&port->state) != IPC_PORT_STATE_ACTIVE || (owner_pid && port->owner_pid != owner_pid))
+        {
+            continue;
+        }
+
+        memset(stats, 0, sizeof(*stats));
+        stats->port = port->cap_id;
+        stats->owner_pid = port->owner_pid;
+        stats->messages = atomic_load(&port->msgs_received);
+        stats->bytes = atomic_load(&port->bytes_transferred);
+        stats->queued = atomic_load(&port->queued);
+        stats->queued_max = atomic_load(&port->queued_max);
+        stats->dropped = atomic_load(&port->msgs_dropped);
+        stats->wait_ns_total = atomic_load(&port->wait_ns_total);
+        stats->wait_ns_max = atomic_load(&port->wait_ns_max);
+        stats->handled = atomic_load(&port->handled);
+        stats->handler_ns_total = atomic_load(&port->handler_ns_total);
+        stats->handler_ns_max = atomic_load(&port->handler_ns_max);
+        for (uint32_t b = 0; b < IPC_STATS_BUCKETS; b++)
+        {
+            stats->wait_buckets[b] = atomic_load(&port->wait_buckets[b]);
+            stats->handler_buckets[b] = atomic_load(&port->handler_buckets[b]);
+        }
+        return (int64_t)(i + 1);
+    }
+    return -OR_ENOENT;
+}
@@ -27,6 +27,9 @@ extern "C"
     int ipc_send_message(ipc_port_t *port, const void *data, size_t size);
     int ipc_receive_message(ipc_port_t *port, void *data, size_t size);
 
+    // Destroy every port of an exiting process, failing its pending calls
+    void ipc_release_ports(uint64_t owner_pid);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,189 @@
+/*
+ * Orion Operating System - IPC Round-Trip Benchmark
+ *
+ * Latency of a request/reply round trip between a client and a server
+ * thread, first with the send+receive pattern (the client sends on the
+ * server's port and waits on a reply port of its own, the server answers
+ * with a send), then with ipc_call and ipc_reply_recv. Messages are the
+ * size of a typical file system or network request header. Run from the
+ * shell with `benchmark ipc [iterations]`.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/structures.h>
+
+extern or_cap_t ipc_port_create(uint64_t owner_pid);
+extern void ipc_port_destroy(or_cap_t port_cap);
+extern int ipc_send_message(or_cap_t port_cap, const void *data, size_t size, uint64_t timeout_ns);
+extern int ipc_recv_message(or_cap_t port_cap, void *buffer, size_t buffer_size, uint64_t timeout_ns);
+extern int ipc_call(or_cap_t port_cap, const void *request, size_t request_size, void *reply, size_t reply_size,
+                    uint64_t timeout_ns);
+extern int ipc_reply_recv(or_cap_t port_cap, const void *reply, size_t reply_size, void *request,
+                          size_t request_size);
+extern void thread_exit(int exit_code);
+
+#define IPC_BENCH_DEFAULT_ITERATIONS 10000
+#define IPC_BENCH_WARMUP 100
+#define IPC_BENCH_MESSAGE_SIZE 64
+#define IPC_BENCH_TIMEOUT_NS 1000000000ULL
+
+typedef struct ipc_bench_ctx
+{
+    or_cap_t server_port;
+    or_cap_t reply_port; // Send+receive pattern only
+    bool use_call;
+    uint32_t rounds;
+} ipc_bench_ctx_t;
+
+typedef struct ipc_bench_result
+{
+    uint64_t min_ns;
+    uint64_t avg_ns;
+    uint64_t max_ns;
+} ipc_bench_result_t;
+
+// Echo server. The context lives on the client's stack, which may be gone
+// once the last reply is sent, so it is copied first
+static void ipc_bench_server(uint64_t arg)
+{
+    ipc_bench_ctx_t ctx = *(const ipc_bench_ctx_t *)arg;
+    uint8_t request[IPC_BENCH_MESSAGE_SIZE];
+
+    if (ctx.use_call)
+    {
+        // Runs until the port is destroyed under the parked server
+        int size = ipc_reply_recv(ctx.server_port, NULL, 0, request, sizeof(request));
+        while (size >= 0)
+        {
+            size = ipc_reply_recv(ctx.server_port, request, (size_t)size, request, sizeof(request));
+        }
+    }
+    else
+    {
+        for (uint32_t i = 0; i < ctx.rounds; i++)
+        {
+            int size = ipc_recv_message(ctx.server_port, request, sizeof(request), IPC_BENCH_TIMEOUT_NS);
+            if (size < 0 || ipc_send_message(ctx.reply_port, request, (size_t)size, IPC_BENCH_TIMEOUT_NS) != OR_OK)
+            {
+                break;
+            }
+        }
+    }
+
+    thread_exit(0);
+}
+
+static int ipc_bench_run(bool use_call, uint32_t iterations, ipc_bench_result_t *result)
+{
+    process_t *client = scheduler_get_current_process();
+    if (!client)
+    {
+        return -OR_EPERM;
+    }
+
+    // The server thread exits on its own; its process is reaped like any
+    // other zombie
+    process_t *server_process = scheduler_create_process();
+    if (!server_process)
+    {
+        return -OR_ENOMEM;
+    }
+
+    ipc_bench_ctx_t ctx = {0};
+    ctx.use_call = use_call;
+    ctx.rounds = iterations + IPC_BENCH_WARMUP;
+    ctx.server_port = ipc_port_create(server_process->pid);
+    ctx.reply_port = use_call ? 0 : ipc_port_create(client->pid);
+    thread_t *server = NULL;
+    if (ctx.server_port && (use_call || ctx.reply_port))
+    {
+        server = scheduler_create_thread(server_process, (uint64_t)ipc_bench_server, 0, (uint64_t)&ctx);
+    }
+    if (!server)
+    {
+        ipc_port_destroy(ctx.server_port);
+        ipc_port_destroy(ctx.reply_port);
+        scheduler_destroy_process(server_process);
+        return -OR_ENOMEM;
+    }
+    scheduler_add_thread_to_rq(server);
+
+    uint8_t request[IPC_BENCH_MESSAGE_SIZE];
+    uint8_t reply[IPC_BENCH_MESSAGE_SIZE];
+    memset(request, 0xA5, sizeof(request));
+
+    uint64_t total = 0;
+    result->min_ns = UINT64_MAX;
+    result->max_ns = 0;
+    int status = OR_OK;
+    for (uint32_t i = 0; i < ctx.rounds && status >= 0; i++)
+    {
+        uint64_t start = arch_get_timestamp();
+        if (use_call)
+        {
+            status = ipc_call(ctx.server_port, request, sizeof(request), reply, sizeof(reply), IPC_BENCH_TIMEOUT_NS);
+        }
+        else
+        {
+            status = ipc_send_message(ctx.server_port, request, sizeof(request), IPC_BENCH_TIMEOUT_NS);
+            if (status >= 0)
+            {
+                status = ipc_recv_message(ctx.reply_port, reply, sizeof(reply), IPC_BENCH_TIMEOUT_NS);
+            }
+        }
+        uint64_t elapsed = arch_get_timestamp() - start;
+
+        if (i >= IPC_BENCH_WARMUP)
+        {
+            total += elapsed;
+            result->min_ns = MIN(result->min_ns, elapsed);
+            result->max_ns = MAX(result->max_ns, elapsed);
+        }
+    }
+    result->avg_ns = total / iterations;
+
+    ipc_port_destroy(ctx.server_port);
+    ipc_port_destroy(ctx.reply_port);
+    return status < 0 ? status : OR_OK;
+}
+
+int ipc_benchmark(uint32_t iterations)
+{
+    if (iterations == 0)
+    {
+        iterations = IPC_BENCH_DEFAULT_ITERATIONS;
+    }
+
+    ipc_bench_result_t send_recv;
+    ipc_bench_result_t call_reply;
+    int result = ipc_bench_run(false, iterations, &send_recv);
+    if (result == OR_OK)
+    {
+        result = ipc_bench_run(true, iterations, &call_reply);
+    }
+    if (result != OR_OK)
+    {
+        kerror("IPC benchmark failed: %d", result);
+        return result;
+    }
+
+    kinfo("IPC round trip, %u bytes, %u iterations:", IPC_BENCH_MESSAGE_SIZE, iterations);
+    kinfo("  send+recv:  avg %llu ns, min %llu ns, max %llu ns", (unsigned long long)send_recv.avg_ns,
+          (unsigned long long)send_recv.min_ns, (unsigned long long)send_recv.max_ns);
+    kinfo("  call/reply: avg %llu ns, min %llu ns, max %llu ns", (unsigned long long)call_reply.avg_ns,
+          (unsigned long long)call_reply.min_ns, (unsigned long long)call_reply.max_ns);
+    if (call_reply.avg_ns)
+    {
+        uint64_t speedup = send_recv.avg_ns * 100 / call_reply.avg_ns;
+        kinfo("  call/reply speedup: %llu.%02llux", (unsigned long long)(speedup / 100),
+              (unsigned long long)(speedup % 100));
+    }
+    return OR_OK;
+}
@@ -0,0 +1,68 @@
+/*
+ * Orion Operating System - IPC Endpoint Statistics
+ *
+ * What each IPC port sees of the traffic it serves: messages and calls
+ * received, how many wait at once, how long they waited before their
+ * server took them, how long the server spent on them, and how many were
+ * dropped (a send timing out on a full queue, a call timing out before
+ * it was taken, a request too large for the server's buffer, the calls
+ * failed when the port goes away).
+ *
+ * Times are in nanoseconds. Besides their total and maximum they are
+ * counted in a histogram of IPC_STATS_BUCKETS buckets, each ten times
+ * wider than the one before: up to 1 us, 10 us, ... 1 s, and above.
+ * Bucket counts are per bucket, not cumulative.
+ *
+ * SYS_IPC_STATS hands them out one port at a time; the metrics exporter
+ * serves them per server.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_IPC_STATS_H
+#define ORION_IPC_STATS_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Latency histogram: upper bound of the first bucket, and bucket count
+// (the last one has no upper bound)
+#define IPC_STATS_FIRST_BOUND_NS 1000ULL
+#define IPC_STATS_BUCKETS 8
+
+    typedef struct ipc_port_stats
+    {
+        uint64_t port;       // Capability of the port
+        uint64_t owner_pid;  // Process serving it
+        uint64_t messages;   // Messages and calls received
+        uint64_t bytes;      // Bytes carried, requests and replies
+        uint64_t queued;     // Waiting for the server now
+        uint64_t queued_max; // Most waiting at once
+        uint64_t dropped;    // Never served
+        uint64_t wait_ns_total;
+        uint64_t wait_ns_max;
+        uint64_t wait_buckets[IPC_STATS_BUCKETS];
+        uint64_t handled; // Requests the server finished with
+        uint64_t handler_ns_total;
+        uint64_t handler_ns_max;
+        uint64_t handler_buckets[IPC_STATS_BUCKETS];
+    } ipc_port_stats_t;
+
+    // Statistics of the first active port at or after `cursor` (0 for the
+    // first), only among the ports of `owner_pid` unless it is 0. Returns
+    // the cursor of the next port, -OR_ENOENT past the last one
+    int64_t ipc_get_port_stats(uint64_t cursor, uint64_t owner_pid, ipc_port_stats_t *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_IPC_STATS_H
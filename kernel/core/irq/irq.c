@@ -0,0 +1,259 @@
+/*
+ * Orion Operating System - Interrupt Controller Abstraction (orion-irq)
+ *
+ * Handler table and dispatch loop shared by all architectures. The table
+ * is only changed under its lock; dispatch reads a line's handler and
+ * data once, so a handler being freed concurrently runs at most once
+ * more.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/irq.h>
+
+typedef struct irq_desc
+{
+    irq_handler_t handler;
+    void *data;
+    char name[IRQ_NAME_MAX];
+    irq_stats_t stats;
+} irq_desc_t;
+
+static irq_desc_t g_irq_descs[IRQ_MAX];
+static const irq_chip_t *g_irq_chip = NULL;
+static spinlock_t g_irq_lock = SPINLOCK_INIT;
+
+// Interrupts handled at once before returning from the vector, so that a
+// stuck line cannot hold the CPU forever
+#define IRQ_DISPATCH_BUDGET 64
+
+void irq_init(void)
+{
+    memset(g_irq_descs, 0, sizeof(g_irq_descs));
+    g_irq_chip = NULL;
+    kinfo("IRQ: interrupt layer ready, %u lines", IRQ_MAX);
+}
+
+int irq_register_chip(const irq_chip_t *chip)
+{
+    if (!chip || !chip->enable || !chip->disable || !chip->ack || !chip->eoi)
+    {
+        return -OR_EINVAL;
+    }
+    spin_lock(&g_irq_lock);
+    if (g_irq_chip)
+    {
+        spin_unlock(&g_irq_lock);
+        kerror("IRQ: %s already registered, refusing %s", g_irq_chip->name, chip->name);
+        return -OR_EBUSY;
+    }
+    g_irq_chip = chip;
+    spin_unlock(&g_irq_lock);
+
+    kinfo("IRQ: controller %s, %u lines", chip->name, chip->nr_irqs);
+    return OR_OK;
+}
+
+const irq_chip_t *irq_get_chip(void)
+{
+    return g_irq_chip;
+}
+
+static bool irq_valid(uint32_t irq)
+{
+    return g_irq_chip && irq < IRQ_MAX && irq < g_irq_chip->nr_irqs;
+}
+
+int irq_request(uint32_t irq, irq_handler_t handler, void *data, const char *name)
+{
+    if (!handler || !irq_valid(irq))
+    {
+        return -OR_EINVAL;
+    }
+
+    spin_lock(&g_irq_lock);
+    irq_desc_t *desc = &g_irq_descs[irq];
+    if (desc->handler)
+    {
+        spin_unlock(&g_irq_lock);
+        kwarn("IRQ: line %u already taken by %s", irq, desc->name);
+        return -OR_EBUSY;
+    }
+    desc->data = data;
+    desc->handler = handler;
+    snprintf(desc->name, IRQ_NAME_MAX, "%s", name ? name : "unnamed");
+    memset(&desc->stats, 0, sizeof(desc->stats));
+    spin_unlock(&g_irq_lock);
+
+    g_irq_chip->enable(irq);
+    kdebug("IRQ: line %u -> %s", irq, desc->name);
+    return OR_OK;
+}
+
+int irq_free(uint32_t irq)
+{
+    if (!irq_valid(irq))
+    {
+        return -OR_EINVAL;
+    }
+
+    g_irq_chip->disable(irq);
+    spin_lock(&g_irq_lock);
+    g_irq_descs[irq].handler = NULL;
+    g_irq_descs[irq].data = NULL;
+    spin_unlock(&g_irq_lock);
+    return OR_OK;
+}
+
+void irq_enable(uint32_t irq)
+{
+    if (irq_valid(irq))
+    {
+        g_irq_chip->enable(irq);
+    }
+}
+
+void irq_disable(uint32_t irq)
+{
+    if (irq_valid(irq))
+    {
+        g_irq_chip->disable(irq);
+    }
+}
+
+int irq_set_type(uint32_t irq, uint32_t type)
+{
+    if (!irq_valid(irq))
+    {
+        return -OR_EINVAL;
+    }
+    return g_irq_chip->set_type ? g_irq_chip->set_type(irq, type) : -OR_ENOTSUP;
+}
+
+int irq_set_priority(uint32_t irq, uint8_t priority)
+{
+    if (!irq_valid(irq))
+    {
+        return -OR_EINVAL;
+    }
+    return g_irq_chip->set_priority ? g_irq_chip->set_priority(irq, priority) : -OR_ENOTSUP;
+}
+
+int irq_set_affinity(uint32_t irq, uint32_t cpu)
+{
+    if (!irq_valid(irq))
+    {
+        return -OR_EINVAL;
+    }
+    return g_irq_chip->set_affinity ? g_irq_chip->set_affinity(irq, cpu) : -OR_ENOTSUP;
+}
+
+int irq_send_ipi(uint32_t irq, uint32_t cpu)
+{
+    if (!irq_valid(irq))
+    {
+        return -OR_EINVAL;
+    }
+    return g_irq_chip->send_ipi ? g_irq_chip->send_ipi(irq, cpu) : -OR_ENOTSUP;
+}
+
+int irq_cpu_init(uint32_t cpu)
+{
+    if (!g_irq_chip)
+    {
+        return -OR_EINVAL;
+    }
+    return g_irq_chip->cpu_init ? g_irq_chip->cpu_init(cpu) : OR_OK;
+}
+
+int irq_xlate(const uint32_t *cells, uint32_t count, uint32_t *irq, uint32_t *type)
+{
+    if (!cells || count == 0 || !irq || !type)
+    {
+        return -OR_EINVAL;
+    }
+    if (g_irq_chip && g_irq_chip->xlate)
+    {
+        return g_irq_chip->xlate(cells, count, irq, type);
+    }
+    // One-cell specifiers name the line directly
+    *irq = cells[0];
+    *type = IRQ_TYPE_LEVEL_HIGH;
+    return OR_OK;
+}
+
+// ========================================
+// DISPATCH
+// ========================================
+
+void irq_handle(void)
+{
+    const irq_chip_t *chip = g_irq_chip;
+    if (!chip)
+    {
+        return;
+    }
+
+    for (int budget = 0; budget < IRQ_DISPATCH_BUDGET; budget++)
+    {
+        uint32_t irq = chip->ack();
+        if (irq == IRQ_NONE)
+        {
+            return;
+        }
+        if (irq >= IRQ_MAX)
+        {
+            chip->eoi(irq);
+            continue;
+        }
+
+        irq_desc_t *desc = &g_irq_descs[irq];
+        irq_handler_t handler = desc->handler;
+        void *data = desc->data;
+        if (handler)
+        {
+            desc->stats.count++;
+            desc->stats.last_cpu = arch_get_current_cpu();
+            handler(irq, data);
+        }
+        else
+        {
+            // Nobody asked for this line: mask it rather than take it again
+            desc->stats.spurious++;
+            chip->disable(irq);
+            kwarn("IRQ: spurious interrupt %u, line masked", irq);
+        }
+        chip->eoi(irq);
+    }
+}
+
+bool irq_get_stats(uint32_t irq, irq_stats_t *out)
+{
+    if (irq >= IRQ_MAX || !out)
+    {
+        return false;
+    }
+    *out = g_irq_descs[irq].stats;
+    return true;
+}
+
+void irq_dump(void)
+{
+    kinfo("IRQ: controller %s", g_irq_chip ? g_irq_chip->name : "none");
+    for (uint32_t irq = 0; irq < IRQ_MAX; irq++)
+    {
+        irq_desc_t *desc = &g_irq_descs[irq];
+        if (desc->handler || desc->stats.spurious)
+        {
+            kinfo("  %4u %-20s %10llu (spurious %llu, last CPU %u)", irq, desc->handler ? desc->name : "-",
+                  (unsigned long long)desc->stats.count, (unsigned long long)desc->stats.spurious,
+                  desc->stats.last_cpu);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * Orion Operating System - Interrupt Controller Abstraction (orion-irq)
+ *
+ * Architecture-independent interrupt layer. An interrupt controller driver
+ * (GICv3, PLIC, APIC) registers an irq_chip describing how to enable, mask,
+ * acknowledge and complete its lines; drivers and the kernel attach
+ * handlers to interrupt numbers through irq_request and never touch the
+ * controller. The architecture's interrupt vector calls irq_handle, which
+ * acknowledges pending interrupts at the chip, runs their handlers and
+ * signals completion until nothing is pending.
+ *
+ * Interrupt numbers are the chip's own: on GICv3 SGIs are 0-15, PPIs
+ * 16-31 and SPIs start at 32.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_IRQ_H
+#define ORION_IRQ_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define IRQ_MAX 1024
+#define IRQ_NAME_MAX 32
+
+// Returned by irq_chip.ack when no interrupt is pending
+#define IRQ_NONE 0xFFFFFFFFU
+
+// Trigger types accepted by irq_set_type
+#define IRQ_TYPE_LEVEL_HIGH 0x0
+#define IRQ_TYPE_EDGE_RISING 0x1
+
+// Priorities: lower values are more urgent
+#define IRQ_PRIORITY_HIGHEST 0x00
+#define IRQ_PRIORITY_DEFAULT 0xA0
+
+    typedef void (*irq_handler_t)(uint32_t irq, void *data);
+
+    // Operations of an interrupt controller. Optional entries may be NULL
+    typedef struct irq_chip
+    {
+        const char *name;
+        uint32_t nr_irqs;
+
+        void (*enable)(uint32_t irq);
+        void (*disable)(uint32_t irq);
+        // Claim the highest priority pending interrupt, IRQ_NONE if none
+        uint32_t (*ack)(void);
+        // Signal the end of an interrupt returned by ack
+        void (*eoi)(uint32_t irq);
+
+        int (*set_type)(uint32_t irq, uint32_t type);
+        int (*set_priority)(uint32_t irq, uint8_t priority);
+        int (*set_affinity)(uint32_t irq, uint32_t cpu);
+        int (*send_ipi)(uint32_t irq, uint32_t cpu);
+        // Bring up the per-CPU part of the controller on a secondary CPU
+        int (*cpu_init)(uint32_t cpu);
+        // Turn a device tree "interrupts" specifier into a line and type
+        int (*xlate)(const uint32_t *cells, uint32_t count, uint32_t *irq, uint32_t *type);
+    } irq_chip_t;
+
+    typedef struct irq_stats
+    {
+        uint64_t count;
+        uint64_t spurious;
+        uint32_t last_cpu;
+    } irq_stats_t;
+
+    void irq_init(void);
+
+    // Install the system interrupt controller. Only one chip is active
+    int irq_register_chip(const irq_chip_t *chip);
+    const irq_chip_t *irq_get_chip(void);
+
+    // Attach a handler to `irq` and unmask it. A line has a single handler
+    int irq_request(uint32_t irq, irq_handler_t handler, void *data, const char *name);
+    int irq_free(uint32_t irq);
+
+    void irq_enable(uint32_t irq);
+    void irq_disable(uint32_t irq);
+    int irq_set_type(uint32_t irq, uint32_t type);
+    int irq_set_priority(uint32_t irq, uint8_t priority);
+    int irq_set_affinity(uint32_t irq, uint32_t cpu);
+    int irq_send_ipi(uint32_t irq, uint32_t cpu);
+    int irq_cpu_init(uint32_t cpu);
+    int irq_xlate(const uint32_t *cells, uint32_t count, uint32_t *irq, uint32_t *type);
+
+    // Called by the architecture's interrupt vector with interrupts masked
+    void irq_handle(void);
+
+    bool irq_get_stats(uint32_t irq, irq_stats_t *out);
+    void irq_dump(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_IRQ_H
@@ -0,0 +1,71 @@
+/*
+ * Orion Operating System - Allocator Per-CPU Caches
+ *
+ * Each CPU keeps a magazine of free objects per size class. Allocations
+ * and releases go to the magazine of the running CPU; an empty magazine
+ * is refilled, and a full one drained, by half its capacity at once from
+ * the central slabs, so the shared lock is taken once every few
+ * operations.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::ptr;
+
+use crate::class::NUM_CLASSES;
+
+/// Objects cached per CPU and class
+pub const MAGAZINE_SIZE: usize = 16;
+
+/// Objects moved between a magazine and the central slabs at once
+pub const BATCH: usize = MAGAZINE_SIZE / 2;
+
+#[derive(Clone, Copy)]
+pub struct Magazine {
+    objects: [*mut u8; MAGAZINE_SIZE],
+    count: usize,
+}
+
+impl Magazine {
+    const EMPTY: Magazine = Magazine { objects: [ptr::null_mut(); MAGAZINE_SIZE], count: 0 };
+
+    pub fn pop(&mut self) -> Option<*mut u8> {
+        if self.count == 0 {
+            return None;
+        }
+        self.count -= 1;
+        Some(self.objects[self.count])
+    }
+
+    /// Cache `object`; false when the magazine is full
+    pub fn push(&mut self, object: *mut u8) -> bool {
+        if self.count == MAGAZINE_SIZE {
+            return false;
+        }
+        self.objects[self.count] = object;
+        self.count += 1;
+        true
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.count
+    }
+}
+
+pub struct CpuCache {
+    pub magazines: [Magazine; NUM_CLASSES],
+}
+
+// The cached objects belong to the cache, reached under its lock
+unsafe impl Send for CpuCache {}
+
+impl CpuCache {
+    pub const fn new() -> Self {
+        Self { magazines: [Magazine::EMPTY; NUM_CLASSES] }
+    }
+}
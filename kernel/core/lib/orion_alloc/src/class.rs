@@ -0,0 +1,55 @@
+/*
+ * Orion Operating System - Allocator Size Classes
+ *
+ * Small requests are rounded up to one of a fixed set of sizes, spaced so
+ * that no more than a third of an object is wasted. Every power of two is
+ * a class: requests aligned beyond MIN_ALIGN take the power of two
+ * covering both their size and alignment, whose objects are naturally
+ * aligned within their page-aligned slab.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+pub const NUM_CLASSES: usize = 14;
+
+pub const CLASS_SIZES: [usize; NUM_CLASSES] = [16, 32, 48, 64, 96, 128, 192, 256, 384, 512, 768, 1024, 1536, 2048];
+
+/// Largest request served from a slab
+pub const MAX_SMALL_SIZE: usize = 2048;
+
+/// Alignment of every slab object
+pub const MIN_ALIGN: usize = 16;
+
+/// Size class serving `size` bytes aligned to `align`, or None when the
+/// request goes to the page allocator
+pub fn class_of(size: usize, align: usize) -> Option<usize> {
+    let needed = if align <= MIN_ALIGN { size.max(1) } else { size.max(align).checked_next_power_of_two()? };
+    if needed > MAX_SMALL_SIZE {
+        return None;
+    }
+    CLASS_SIZES.iter().position(|&class_size| class_size >= needed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picks_smallest_fitting_class() {
+        assert_eq!(class_of(0, 1), Some(0));
+        assert_eq!(class_of(16, 8), Some(0));
+        assert_eq!(class_of(17, 8), Some(1));
+        assert_eq!(CLASS_SIZES[class_of(100, 16).unwrap()], 128);
+        assert_eq!(class_of(2048, 16), Some(NUM_CLASSES - 1));
+        assert_eq!(class_of(2049, 16), None);
+
+        // Over-aligned requests take a power of two
+        assert_eq!(CLASS_SIZES[class_of(40, 64).unwrap()], 64);
+        assert_eq!(CLASS_SIZES[class_of(100, 64).unwrap()], 128);
+        assert_eq!(class_of(16, 4096), None);
+    }
+}
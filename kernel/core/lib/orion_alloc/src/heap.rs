@@ -0,0 +1,317 @@
+/*
+ * Orion Operating System - Allocator Heap
+ *
+ * The global allocator: size classes served through the per-CPU caches,
+ * page-sized requests through the region, statistics and the leak
+ * tracking hook. The running CPU is given by a function the program
+ * installs, CPU 0 until then; a single-threaded server needs none.
+ * Locks are always taken cache first, then the central state.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::cache::{CpuCache, BATCH};
+use crate::class::class_of;
+use crate::lock::SpinLock;
+use crate::region::{Region, PAGE_SIZE};
+use crate::slab::Slabs;
+use crate::stats::{AllocStats, Stats};
+
+/// CPUs with their own cache; CPU ids wrap around beyond
+pub const MAX_CPUS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Alloc,
+    Free,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocEvent {
+    pub kind: EventKind,
+    pub address: usize,
+    pub size: usize,
+    pub align: usize,
+}
+
+/// Sees every allocation and release. Runs inside the allocator: it must
+/// not allocate
+pub type AllocHook = fn(&AllocEvent);
+
+struct Central {
+    region: Region,
+    slabs: Slabs,
+}
+
+pub struct OrionHeap {
+    central: SpinLock<Central>,
+    caches: [SpinLock<CpuCache>; MAX_CPUS],
+    stats: Stats,
+    hook: AtomicUsize,
+    cpu_id: AtomicUsize,
+}
+
+impl Default for OrionHeap {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl OrionHeap {
+    /// Heap without memory; every allocation fails until `init`
+    pub const fn empty() -> Self {
+        Self {
+            central: SpinLock::new(Central { region: Region::new(), slabs: Slabs::new() }),
+            caches: [const { SpinLock::new(CpuCache::new()) }; MAX_CPUS],
+            stats: Stats::new(),
+            hook: AtomicUsize::new(0),
+            cpu_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Hand `size` bytes at `start` to the heap. May be called again to
+    /// add memory
+    ///
+    /// # Safety
+    /// The memory must be unused, writable and owned by the heap from now
+    /// on.
+    pub unsafe fn init(&self, start: *mut u8, size: usize) {
+        self.central.lock().region.add(start, size);
+    }
+
+    /// Install the function returning the id of the running CPU
+    pub fn set_cpu_id(&self, cpu_id: fn() -> usize) {
+        self.cpu_id.store(cpu_id as usize, Ordering::Relaxed);
+    }
+
+    pub fn set_hook(&self, hook: Option<AllocHook>) {
+        self.hook.store(hook.map_or(0, |hook| hook as usize), Ordering::Release);
+    }
+
+    pub fn stats(&self) -> AllocStats {
+        let (heap_bytes, heap_free_bytes) = {
+            let central = self.central.lock();
+            (central.region.total_bytes(), central.region.free_bytes())
+        };
+        self.stats.snapshot(heap_bytes, heap_free_bytes)
+    }
+
+    /// Slab spans held by each size class
+    pub fn spans(&self, class: usize) -> usize {
+        self.central.lock().slabs.spans(class)
+    }
+
+    fn current_cpu(&self) -> usize {
+        match self.cpu_id.load(Ordering::Relaxed) {
+            0 => 0,
+            function => {
+                let cpu_id: fn() -> usize = unsafe { core::mem::transmute(function) };
+                cpu_id() % MAX_CPUS
+            }
+        }
+    }
+
+    fn emit(&self, kind: EventKind, address: *mut u8, layout: Layout) {
+        let hook = self.hook.load(Ordering::Acquire);
+        if hook != 0 {
+            let hook: AllocHook = unsafe { core::mem::transmute(hook) };
+            hook(&AllocEvent { kind, address: address as usize, size: layout.size(), align: layout.align() });
+        }
+    }
+
+    fn alloc_small(&self, class: usize) -> *mut u8 {
+        let mut cache = self.caches[self.current_cpu()].lock();
+        let magazine = &mut cache.magazines[class];
+        if let Some(object) = magazine.pop() {
+            return object;
+        }
+
+        let mut central = self.central.lock();
+        let Central { region, slabs } = &mut *central;
+        for _ in 0..BATCH {
+            let object = slabs.pop(class, region);
+            if object.is_null() {
+                break;
+            }
+            magazine.push(object);
+        }
+        magazine.pop().unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn free_small(&self, class: usize, object: *mut u8) {
+        let mut cache = self.caches[self.current_cpu()].lock();
+        let magazine = &mut cache.magazines[class];
+        if magazine.push(object) {
+            return;
+        }
+
+        let mut central = self.central.lock();
+        for _ in 0..BATCH {
+            if let Some(cached) = magazine.pop() {
+                central.slabs.push(class, cached);
+            }
+        }
+        magazine.push(object);
+    }
+}
+
+unsafe impl GlobalAlloc for OrionHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = class_of(layout.size(), layout.align());
+        let address = match class {
+            Some(class) => self.alloc_small(class),
+            None => self.central.lock().region.alloc(layout.size(), layout.align()),
+        };
+        if address.is_null() {
+            self.stats.failed();
+            return address;
+        }
+        self.stats.allocated(class, layout.size());
+        self.emit(EventKind::Alloc, address, layout);
+        address
+    }
+
+    unsafe fn dealloc(&self, address: *mut u8, layout: Layout) {
+        let class = class_of(layout.size(), layout.align());
+        self.emit(EventKind::Free, address, layout);
+        self.stats.freed(class, layout.size());
+        match class {
+            Some(class) => self.free_small(class, address),
+            None => self.central.lock().region.free(address, layout.size()),
+        }
+    }
+
+    unsafe fn realloc(&self, address: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let class = class_of(layout.size(), layout.align());
+        // Growing or shrinking within the same object or pages keeps it
+        let fits = match (class, class_of(new_size, layout.align())) {
+            (Some(old), Some(new)) => old == new,
+            (None, None) => layout.size().div_ceil(PAGE_SIZE) == new_size.div_ceil(PAGE_SIZE),
+            _ => false,
+        };
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        if fits {
+            self.emit(EventKind::Free, address, layout);
+            self.stats.freed(class, layout.size());
+            self.stats.allocated(class, new_size);
+            self.emit(EventKind::Alloc, address, new_layout);
+            return address;
+        }
+
+        let new_address = self.alloc(new_layout);
+        if !new_address.is_null() {
+            ptr::copy_nonoverlapping(address, new_address, layout.size().min(new_size));
+            self.dealloc(address, layout);
+        }
+        new_address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MAGAZINE_SIZE;
+    use crate::class::CLASS_SIZES;
+    use alloc::alloc::{alloc, dealloc};
+    use alloc::vec::Vec;
+
+    const ARENA_SIZE: usize = 256 * PAGE_SIZE;
+
+    fn with_heap(test: impl FnOnce(&OrionHeap)) {
+        let layout = Layout::from_size_align(ARENA_SIZE, PAGE_SIZE).unwrap();
+        let arena = unsafe { alloc(layout) };
+        let heap = OrionHeap::empty();
+        unsafe { heap.init(arena, ARENA_SIZE) };
+        test(&heap);
+        unsafe { dealloc(arena, layout) };
+    }
+
+    #[test]
+    fn test_serves_classes_and_pages() {
+        with_heap(|heap| {
+            let small = Layout::from_size_align(40, 8).unwrap();
+            let large = Layout::from_size_align(3 * PAGE_SIZE, 8).unwrap();
+            let mut objects = Vec::new();
+            for _ in 0..3 * MAGAZINE_SIZE {
+                let object = unsafe { heap.alloc(small) };
+                assert!(!object.is_null() && (object as usize).is_multiple_of(16));
+                objects.push(object);
+            }
+            let pages = unsafe { heap.alloc(large) };
+            assert_eq!(pages as usize % PAGE_SIZE, 0);
+
+            let stats = heap.stats();
+            let class = class_of(40, 8).unwrap();
+            assert_eq!(stats.classes[class].live, 3 * MAGAZINE_SIZE as u64);
+            assert_eq!(stats.large.live, 1);
+            assert_eq!(stats.bytes_in_use, (3 * MAGAZINE_SIZE * 40 + 3 * PAGE_SIZE) as u64);
+
+            // Released objects are handed out again, through the cache or
+            // the central slabs once the cache is full
+            for &object in &objects {
+                unsafe { heap.dealloc(object, small) };
+            }
+            unsafe { heap.dealloc(pages, large) };
+            let again = unsafe { heap.alloc(small) };
+            assert!(objects.contains(&again));
+            unsafe { heap.dealloc(again, small) };
+            assert_eq!(heap.spans(class), 1);
+
+            let stats = heap.stats();
+            assert_eq!(stats.live_allocations(), 0);
+            assert_eq!(stats.classes[class].peak_live, 3 * MAGAZINE_SIZE as u64);
+            assert_eq!(stats.peak_bytes_in_use, (3 * MAGAZINE_SIZE * 40 + 3 * PAGE_SIZE) as u64);
+            assert_eq!(CLASS_SIZES[class], 48);
+        });
+    }
+
+    static EVENTS: AtomicUsize = AtomicUsize::new(0);
+    static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+    fn track(event: &AllocEvent) {
+        EVENTS.fetch_add(1, Ordering::Relaxed);
+        match event.kind {
+            EventKind::Alloc => LIVE.fetch_add(1, Ordering::Relaxed),
+            EventKind::Free => LIVE.fetch_sub(1, Ordering::Relaxed),
+        };
+    }
+
+    fn cpu_three() -> usize {
+        3
+    }
+
+    #[test]
+    fn test_reports_to_hook_and_reallocates_in_place() {
+        with_heap(|heap| {
+            heap.set_hook(Some(track));
+            heap.set_cpu_id(cpu_three);
+            let layout = Layout::from_size_align(20, 4).unwrap();
+            let object = unsafe { heap.alloc(layout) };
+            // 20 and 30 bytes share the 32-byte class
+            assert_eq!(unsafe { heap.realloc(object, layout, 30) }, object);
+            let layout = Layout::from_size_align(30, 4).unwrap();
+            let moved = unsafe { heap.realloc(object, layout, 4000) };
+            assert_ne!(moved, object);
+            assert_eq!(LIVE.load(Ordering::Relaxed), 1);
+            unsafe { heap.dealloc(moved, Layout::from_size_align(4000, 4).unwrap()) };
+            heap.set_hook(None);
+
+            assert_eq!(EVENTS.load(Ordering::Relaxed), 6);
+            assert_eq!(LIVE.load(Ordering::Relaxed), 0);
+            assert_eq!(heap.caches[3].lock().magazines[1].len(), BATCH);
+
+            // Memory that cannot be served is counted
+            let huge = Layout::from_size_align(2 * ARENA_SIZE, 8).unwrap();
+            assert!(unsafe { heap.alloc(huge) }.is_null());
+            assert_eq!(heap.stats().failures, 1);
+        });
+    }
+}
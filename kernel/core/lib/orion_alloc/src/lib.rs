@@ -0,0 +1,41 @@
+/*
+ * Orion Operating System - Memory Allocator
+ *
+ * Global allocator for servers and drivers, replacing the single locked
+ * free list of linked_list_allocator. Requests up to 2 KiB are served
+ * from slabs of fixed-size objects, one per size class, through small
+ * per-CPU caches so that the common path takes an uncontended lock and
+ * touches no shared list. Larger requests get whole pages from a
+ * segregated-fit page allocator, which also feeds the slabs. Every
+ * allocation is counted per size class, and an optional hook sees every
+ * allocation and release for leak tracking.
+ *
+ *     #[global_allocator]
+ *     static ALLOCATOR: OrionHeap = OrionHeap::empty();
+ *
+ *     unsafe { ALLOCATOR.init(arena_start, arena_size) };
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod class;
+pub mod heap;
+pub mod region;
+pub mod stats;
+
+mod cache;
+mod lock;
+mod slab;
+
+pub use class::{class_of, CLASS_SIZES, MAX_SMALL_SIZE, MIN_ALIGN, NUM_CLASSES};
+pub use heap::{AllocEvent, AllocHook, EventKind, OrionHeap, MAX_CPUS};
+pub use region::PAGE_SIZE;
+pub use stats::{AllocStats, ClassStats};
@@ -0,0 +1,181 @@
+/*
+ * Orion Operating System - Allocator Page Region
+ *
+ * Page allocator backing the heap. Memory handed to the heap is carved
+ * from the front of the first region; freed blocks are kept on
+ * segregated free lists, bin n holding blocks of 2^n to 2^(n+1) - 1
+ * pages, and reused first fit, the remainder of a split block going back
+ * to its bin. A block freed right below the carving point is given back
+ * to it. Free blocks store their size and link in their first bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::ptr;
+
+pub const PAGE_SIZE: usize = 4096;
+
+const NUM_BINS: usize = 16;
+
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+pub struct Region {
+    next: usize,
+    end: usize,
+    bins: [*mut FreeBlock; NUM_BINS],
+    total_bytes: usize,
+    free_bytes: usize,
+}
+
+// The free blocks belong to the region, which is only reached under the
+// heap lock
+unsafe impl Send for Region {}
+
+fn round_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+fn bin_of(size: usize) -> usize {
+    let pages = size / PAGE_SIZE;
+    ((usize::BITS - 1 - pages.leading_zeros()) as usize).min(NUM_BINS - 1)
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Region {
+    pub const fn new() -> Self {
+        Self { next: 0, end: 0, bins: [ptr::null_mut(); NUM_BINS], total_bytes: 0, free_bytes: 0 }
+    }
+
+    /// Hand `size` bytes at `start` to the allocator
+    ///
+    /// # Safety
+    /// The memory must be unused, writable and owned by the allocator from
+    /// now on.
+    pub unsafe fn add(&mut self, start: *mut u8, size: usize) {
+        let first = round_up(start as usize, PAGE_SIZE);
+        let last = (start as usize + size) & !(PAGE_SIZE - 1);
+        if last <= first {
+            return;
+        }
+        self.total_bytes += last - first;
+        if self.next == self.end {
+            self.next = first;
+            self.end = last;
+            self.free_bytes += last - first;
+        } else {
+            self.free(first as *mut u8, last - first);
+        }
+    }
+
+    /// Bytes handed to the allocator
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Bytes neither allocated nor holding a slab
+    pub fn free_bytes(&self) -> usize {
+        self.free_bytes
+    }
+
+    /// Whole pages covering `size` bytes, aligned to `align` (at least a
+    /// page); null when the region is exhausted
+    pub fn alloc(&mut self, size: usize, align: usize) -> *mut u8 {
+        let size = round_up(size.max(1), PAGE_SIZE);
+        let align = align.max(PAGE_SIZE);
+
+        for bin in bin_of(size)..NUM_BINS {
+            let mut link: *mut *mut FreeBlock = &mut self.bins[bin];
+            unsafe {
+                while !(*link).is_null() {
+                    let block = *link;
+                    let address = block as usize;
+                    if (*block).size >= size && address.is_multiple_of(align) {
+                        *link = (*block).next;
+                        let remainder = (*block).size - size;
+                        self.free_bytes -= (*block).size;
+                        if remainder != 0 {
+                            self.free((address + size) as *mut u8, remainder);
+                        }
+                        return block as *mut u8;
+                    }
+                    link = &mut (*block).next;
+                }
+            }
+        }
+
+        let start = round_up(self.next, align);
+        if start > self.end || self.end - start < size {
+            return ptr::null_mut();
+        }
+        let skipped = start - self.next;
+        self.next = start + size;
+        self.free_bytes -= size + skipped;
+        if skipped != 0 {
+            unsafe { self.free((start - skipped) as *mut u8, skipped) };
+        }
+        start as *mut u8
+    }
+
+    /// Give back pages obtained from `alloc`
+    ///
+    /// # Safety
+    /// `ptr` and `size` must describe a block allocated from this region
+    /// and not freed since.
+    pub unsafe fn free(&mut self, ptr: *mut u8, size: usize) {
+        let size = round_up(size.max(1), PAGE_SIZE);
+        self.free_bytes += size;
+        if ptr as usize + size == self.next {
+            self.next = ptr as usize;
+            return;
+        }
+        let block = ptr as *mut FreeBlock;
+        let bin = bin_of(size);
+        block.write(FreeBlock { size, next: self.bins[bin] });
+        self.bins[bin] = block;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::alloc::{alloc, dealloc, Layout};
+
+    #[test]
+    fn test_reuses_and_splits_free_blocks() {
+        let layout = Layout::from_size_align(64 * PAGE_SIZE, PAGE_SIZE).unwrap();
+        let arena = unsafe { alloc(layout) };
+        let mut region = Region::new();
+        unsafe { region.add(arena, 64 * PAGE_SIZE) };
+        assert_eq!(region.free_bytes(), 64 * PAGE_SIZE);
+
+        let a = region.alloc(3 * PAGE_SIZE, 1);
+        let b = region.alloc(PAGE_SIZE, 1);
+        assert_eq!(a, arena);
+        assert_eq!(b as usize, arena as usize + 3 * PAGE_SIZE);
+
+        // A freed block is split to serve a smaller request
+        unsafe { region.free(a, 3 * PAGE_SIZE) };
+        let c = region.alloc(PAGE_SIZE + 1, 1);
+        assert_eq!(c, a);
+        assert_eq!(region.alloc(PAGE_SIZE, 1) as usize, a as usize + 2 * PAGE_SIZE);
+
+        // Over-aligned requests skip misaligned space, which stays usable
+        let aligned = region.alloc(PAGE_SIZE, 16 * PAGE_SIZE);
+        assert_eq!(aligned as usize % (16 * PAGE_SIZE), 0);
+        assert_eq!(region.alloc(64 * PAGE_SIZE, 1), ptr::null_mut());
+
+        unsafe { dealloc(arena, layout) };
+    }
+}
@@ -0,0 +1,78 @@
+/*
+ * Orion Operating System - Allocator Slabs
+ *
+ * Central free lists of the size classes. An empty class takes a span of
+ * pages from the region and carves it into objects; objects are linked
+ * through their first word while free. Spans stay with their class.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::ptr;
+
+use crate::class::{CLASS_SIZES, NUM_CLASSES};
+use crate::region::{Region, PAGE_SIZE};
+
+/// Pages taken from the region at once by a class
+pub const SPAN_SIZE: usize = 4 * PAGE_SIZE;
+
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+pub struct Slabs {
+    free: [*mut FreeObject; NUM_CLASSES],
+    spans: [usize; NUM_CLASSES],
+}
+
+// Objects are only reached under the heap lock
+unsafe impl Send for Slabs {}
+
+impl Slabs {
+    pub const fn new() -> Self {
+        Self { free: [ptr::null_mut(); NUM_CLASSES], spans: [0; NUM_CLASSES] }
+    }
+
+    /// Spans held by `class`
+    pub fn spans(&self, class: usize) -> usize {
+        self.spans[class]
+    }
+
+    fn grow(&mut self, class: usize, region: &mut Region) -> bool {
+        let span = region.alloc(SPAN_SIZE, PAGE_SIZE);
+        if span.is_null() {
+            return false;
+        }
+        let size = CLASS_SIZES[class];
+        // Link from the end so that objects are handed out in address order
+        for index in (0..SPAN_SIZE / size).rev() {
+            unsafe { self.push(class, span.add(index * size)) };
+        }
+        self.spans[class] += 1;
+        true
+    }
+
+    /// Take an object of `class`; null when the region is exhausted
+    pub fn pop(&mut self, class: usize, region: &mut Region) -> *mut u8 {
+        if self.free[class].is_null() && !self.grow(class, region) {
+            return ptr::null_mut();
+        }
+        let object = self.free[class];
+        self.free[class] = unsafe { (*object).next };
+        object as *mut u8
+    }
+
+    /// Give back an object of `class`
+    ///
+    /// # Safety
+    /// `object` must come from `pop` on the same class and be free.
+    pub unsafe fn push(&mut self, class: usize, object: *mut u8) {
+        let object = object as *mut FreeObject;
+        object.write(FreeObject { next: self.free[class] });
+        self.free[class] = object;
+    }
+}
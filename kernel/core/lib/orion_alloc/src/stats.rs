@@ -0,0 +1,144 @@
+/*
+ * Orion Operating System - Allocator Statistics
+ *
+ * Counters kept on every allocation and release, per size class and for
+ * page-sized requests, and the snapshot handed to servers exporting them.
+ * Objects sitting in per-CPU caches count as free.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::class::{CLASS_SIZES, NUM_CLASSES};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClassStats {
+    /// Object size, 0 for page-sized requests
+    pub size: usize,
+    pub allocations: u64,
+    pub frees: u64,
+    /// Allocations not released yet
+    pub live: u64,
+    pub peak_live: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub classes: [ClassStats; NUM_CLASSES],
+    pub large: ClassStats,
+    /// Bytes requested and not released yet
+    pub bytes_in_use: u64,
+    pub peak_bytes_in_use: u64,
+    /// Requests the heap could not satisfy
+    pub failures: u64,
+    /// Memory handed to the heap, and the part of it holding neither
+    /// allocations nor slabs
+    pub heap_bytes: u64,
+    pub heap_free_bytes: u64,
+}
+
+impl AllocStats {
+    /// Allocations not released yet, across all sizes
+    pub fn live_allocations(&self) -> u64 {
+        self.classes.iter().map(|class| class.live).sum::<u64>() + self.large.live
+    }
+}
+
+pub(crate) struct Counters {
+    allocations: AtomicU64,
+    frees: AtomicU64,
+    live: AtomicU64,
+    peak_live: AtomicU64,
+}
+
+impl Counters {
+    pub const fn new() -> Self {
+        Self {
+            allocations: AtomicU64::new(0),
+            frees: AtomicU64::new(0),
+            live: AtomicU64::new(0),
+            peak_live: AtomicU64::new(0),
+        }
+    }
+
+    fn allocated(&self) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        let live = self.live.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_live.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn freed(&self) {
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.live.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, size: usize) -> ClassStats {
+        ClassStats {
+            size,
+            allocations: self.allocations.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            live: self.live.load(Ordering::Relaxed),
+            peak_live: self.peak_live.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) struct Stats {
+    classes: [Counters; NUM_CLASSES],
+    large: Counters,
+    bytes_in_use: AtomicU64,
+    peak_bytes_in_use: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Self {
+            classes: [const { Counters::new() }; NUM_CLASSES],
+            large: Counters::new(),
+            bytes_in_use: AtomicU64::new(0),
+            peak_bytes_in_use: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    fn counters(&self, class: Option<usize>) -> &Counters {
+        class.map_or(&self.large, |class| &self.classes[class])
+    }
+
+    pub fn allocated(&self, class: Option<usize>, size: usize) {
+        self.counters(class).allocated();
+        let in_use = self.bytes_in_use.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        self.peak_bytes_in_use.fetch_max(in_use, Ordering::Relaxed);
+    }
+
+    pub fn freed(&self, class: Option<usize>, size: usize) {
+        self.counters(class).freed();
+        self.bytes_in_use.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, heap_bytes: usize, heap_free_bytes: usize) -> AllocStats {
+        let mut classes = [ClassStats::default(); NUM_CLASSES];
+        for (class, stats) in classes.iter_mut().enumerate() {
+            *stats = self.classes[class].snapshot(CLASS_SIZES[class]);
+        }
+        AllocStats {
+            classes,
+            large: self.large.snapshot(0),
+            bytes_in_use: self.bytes_in_use.load(Ordering::Relaxed),
+            peak_bytes_in_use: self.peak_bytes_in_use.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            heap_bytes: heap_bytes as u64,
+            heap_free_bytes: heap_free_bytes as u64,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+/*
+ * Orion Operating System - Blocking Offload
+ *
+ * A task must not block the thread polling every other task. The rare
+ * operation that has to, a synchronous call into a server or firmware,
+ * is wrapped in a job handed to the installed backend, and its result
+ * awaited. HelperQueue is a backend for programs with a helper thread,
+ * which runs the queued jobs one after the other; a program may instead
+ * forward jobs to a service of its own. Without a backend, jobs run on
+ * the spot.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::lock::SpinLock;
+
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs jobs away from the executor thread
+pub trait BlockingBackend: Sync {
+    fn submit(&self, job: Job);
+}
+
+static BACKEND: SpinLock<Option<&'static dyn BlockingBackend>> = SpinLock::new(None);
+static OUTSTANDING: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_blocking_backend(backend: Option<&'static dyn BlockingBackend>) {
+    *BACKEND.lock() = backend;
+}
+
+/// Jobs submitted and not finished yet
+pub fn outstanding() -> usize {
+    OUTSTANDING.load(Ordering::Relaxed)
+}
+
+/// Jobs waiting for a helper thread
+pub struct HelperQueue {
+    jobs: SpinLock<VecDeque<Job>>,
+}
+
+impl Default for HelperQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HelperQueue {
+    pub const fn new() -> Self {
+        Self { jobs: SpinLock::new(VecDeque::new()) }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.jobs.lock().len()
+    }
+
+    /// Run the oldest queued job; false when there was none. The helper
+    /// thread calls it in a loop, sleeping while it returns false
+    pub fn run_next(&self) -> bool {
+        let job = self.jobs.lock().pop_front();
+        match job {
+            Some(job) => {
+                job();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl BlockingBackend for HelperQueue {
+    fn submit(&self, job: Job) {
+        self.jobs.lock().push_back(job);
+    }
+}
+
+struct BlockingState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Output of a job run off the executor thread
+pub struct Blocking<T> {
+    state: Arc<SpinLock<BlockingState<T>>>,
+}
+
+/// Run `job` on the blocking backend
+pub fn spawn_blocking<F, T>(job: F) -> Blocking<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let state = Arc::new(SpinLock::new(BlockingState { output: None, waker: None }));
+    let finished = state.clone();
+    OUTSTANDING.fetch_add(1, Ordering::Relaxed);
+    let job: Job = Box::new(move || {
+        let output = job();
+        let waker = {
+            let mut state = finished.lock();
+            state.output = Some(output);
+            state.waker.take()
+        };
+        OUTSTANDING.fetch_sub(1, Ordering::Relaxed);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+    let backend = *BACKEND.lock();
+    match backend {
+        Some(backend) => backend.submit(job),
+        None => job(),
+    }
+    Blocking { state }
+}
+
+impl<T> Blocking<T> {
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().output.is_some()
+    }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{spawn, yield_now, Executor};
+
+    static HELPER: HelperQueue = HelperQueue::new();
+
+    #[test]
+    fn test_jobs_run_on_the_helper_and_wake_their_task() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+
+        // No backend: the job runs on the spot
+        let inline = spawn_blocking(|| 6 * 7);
+        assert!(inline.is_finished());
+        assert_eq!(executor.block_on(inline), 42);
+
+        set_blocking_backend(Some(&HELPER));
+        let result = executor.block_on(async {
+            let job = spawn_blocking(|| (1..=10u64).product::<u64>());
+            assert_eq!((HELPER.pending(), outstanding()), (1, 1));
+            // Stands in for the helper thread
+            spawn(async {
+                while !HELPER.run_next() {
+                    yield_now().await;
+                }
+            });
+            job.await
+        });
+        set_blocking_backend(None);
+        assert_eq!(result, 3_628_800);
+        assert_eq!(outstanding(), 0);
+    }
+}
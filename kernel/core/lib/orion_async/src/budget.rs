@@ -0,0 +1,64 @@
+/*
+ * Orion Operating System - Cooperative Budget
+ *
+ * A task may only make so much progress per poll. Every operation of the
+ * channels and locks takes a unit; once the task's budget is spent they
+ * report Pending and wake the task again, so that a loop over an always
+ * ready channel returns to the executor and lets other tasks run.
+ * Futures outside any executor run unconstrained. The budget is that of
+ * the one thread running tasks in the process.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+
+/// Operations a task may perform per poll
+pub const TASK_BUDGET: u32 = 128;
+
+const UNCONSTRAINED: u32 = u32::MAX;
+
+static REMAINING: AtomicU32 = AtomicU32::new(UNCONSTRAINED);
+
+/// Give the task about to be polled a fresh budget; returns the budget of
+/// the poll it interrupts
+pub(crate) fn start() -> u32 {
+    REMAINING.swap(TASK_BUDGET, Ordering::Relaxed)
+}
+
+/// Restore the budget saved by `start`; true when the poll spent all of
+/// its own
+pub(crate) fn finish(saved: u32) -> bool {
+    REMAINING.swap(saved, Ordering::Relaxed) == 0
+}
+
+/// Take one unit of the running task's budget. Pending, with the task
+/// woken again, once it is spent. Leaf futures call it first thing in
+/// `poll`
+pub fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    let remaining = REMAINING.load(Ordering::Relaxed);
+    match remaining {
+        UNCONSTRAINED => Poll::Ready(()),
+        0 => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        _ => {
+            REMAINING.store(remaining - 1, Ordering::Relaxed);
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Units left to the running task
+pub fn remaining() -> Option<u32> {
+    match REMAINING.load(Ordering::Relaxed) {
+        UNCONSTRAINED => None,
+        remaining => Some(remaining),
+    }
+}
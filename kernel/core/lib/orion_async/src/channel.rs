@@ -0,0 +1,225 @@
+/*
+ * Orion Operating System - Async Channel
+ *
+ * Bounded first-in first-out queue between tasks. Senders wait while it
+ * is full and receivers while it is empty. Closing it fails further
+ * sends; receivers still get the values queued before the close, then an
+ * error. Every send and receive takes a unit of the task's budget.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::VecDeque;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use crate::budget;
+use crate::lock::SpinLock;
+
+/// The channel is closed; the value could not be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// The channel is closed and empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+struct ChannelState<T> {
+    queue: VecDeque<T>,
+    closed: bool,
+    senders: VecDeque<Waker>,
+    receivers: VecDeque<Waker>,
+}
+
+fn register(waiters: &mut VecDeque<Waker>, waker: &Waker) {
+    if !waiters.iter().any(|waiter| waiter.will_wake(waker)) {
+        waiters.push_back(waker.clone());
+    }
+}
+
+fn wake_all(waiters: VecDeque<Waker>) {
+    for waiter in waiters {
+        waiter.wake();
+    }
+}
+
+pub struct AsyncChannel<T> {
+    state: SpinLock<ChannelState<T>>,
+    capacity: usize,
+}
+
+impl<T> AsyncChannel<T> {
+    /// Channel holding `capacity` values, at least one
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: SpinLock::new(ChannelState {
+                queue: VecDeque::new(),
+                closed: false,
+                senders: VecDeque::new(),
+                receivers: VecDeque::new(),
+            }),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+
+    pub fn close(&self) {
+        let (senders, receivers) = {
+            let mut state = self.state.lock();
+            state.closed = true;
+            (core::mem::take(&mut state.senders), core::mem::take(&mut state.receivers))
+        };
+        wake_all(senders);
+        wake_all(receivers);
+    }
+
+    /// Queue `value` if there is room; given back when full or closed
+    pub fn try_send(&self, value: T) -> Result<(), SendError<T>> {
+        let receivers = {
+            let mut state = self.state.lock();
+            if state.closed || state.queue.len() == self.capacity {
+                return Err(SendError(value));
+            }
+            state.queue.push_back(value);
+            core::mem::take(&mut state.receivers)
+        };
+        wake_all(receivers);
+        Ok(())
+    }
+
+    /// Oldest value, if any
+    pub fn try_recv(&self) -> Option<T> {
+        let (value, senders) = {
+            let mut state = self.state.lock();
+            let value = state.queue.pop_front()?;
+            (value, core::mem::take(&mut state.senders))
+        };
+        wake_all(senders);
+        Some(value)
+    }
+
+    pub fn send(&self, value: T) -> SendFuture<'_, T> {
+        SendFuture { channel: self, value: Some(value) }
+    }
+
+    pub fn recv(&self) -> RecvFuture<'_, T> {
+        RecvFuture { channel: self }
+    }
+}
+
+pub struct SendFuture<'a, T> {
+    channel: &'a AsyncChannel<T>,
+    value: Option<T>,
+}
+
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if budget::poll_proceed(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let value = self.value.take().expect("send polled after completion");
+        let receivers = {
+            let mut state = self.channel.state.lock();
+            if state.closed {
+                return Poll::Ready(Err(SendError(value)));
+            }
+            if state.queue.len() == self.channel.capacity {
+                register(&mut state.senders, cx.waker());
+                drop(state);
+                self.value = Some(value);
+                return Poll::Pending;
+            }
+            state.queue.push_back(value);
+            core::mem::take(&mut state.receivers)
+        };
+        wake_all(receivers);
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct RecvFuture<'a, T> {
+    channel: &'a AsyncChannel<T>,
+}
+
+impl<T> Future for RecvFuture<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if budget::poll_proceed(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let (value, senders) = {
+            let mut state = self.channel.state.lock();
+            match state.queue.pop_front() {
+                Some(value) => (value, core::mem::take(&mut state.senders)),
+                None if state.closed => return Poll::Ready(Err(RecvError)),
+                None => {
+                    register(&mut state.receivers, cx.waker());
+                    return Poll::Pending;
+                }
+            }
+        };
+        wake_all(senders);
+        Poll::Ready(Ok(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::Executor;
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_bounded_channel_paces_sender_and_drains_after_close() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let channel = Rc::new(AsyncChannel::new(2));
+        let sender = {
+            let channel = channel.clone();
+            executor.spawn(async move {
+                for value in 0..5 {
+                    channel.send(value).await.unwrap();
+                    assert!(channel.len() <= 2);
+                }
+                channel.close();
+                channel.send(5).await
+            })
+        };
+        let received = executor.block_on(async {
+            let mut received = Vec::new();
+            while let Ok(value) = channel.recv().await {
+                received.push(value);
+            }
+            received
+        });
+        assert_eq!(received, [0, 1, 2, 3, 4]);
+        assert_eq!(executor.block_on(sender), Err(SendError(5)));
+        assert_eq!(channel.try_send(6), Err(SendError(6)));
+    }
+}
@@ -0,0 +1,146 @@
+/*
+ * Orion Operating System - Deadlock Diagnostics
+ *
+ * While enabled, the async locks record which task holds them and which
+ * lock each waiting task waits for. Every new wait follows the waits-for
+ * edges from the waiting task, through the holders of the lock it waits
+ * for and the locks they wait for in turn; coming back to the waiting
+ * task means none of them can proceed, and the cycle is handed to the
+ * reporter the program installs, which forwards it to its log or trace.
+ * On by default in debug builds.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::lock::SpinLock;
+
+/// A task waiting for a lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitEdge {
+    pub task: u64,
+    /// Address of the lock
+    pub lock: usize,
+    pub name: Option<&'static str>,
+}
+
+/// Tasks waiting for each other: each waits for a lock held by the next,
+/// the last for one held by the first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlockReport {
+    pub cycle: Vec<WaitEdge>,
+}
+
+impl fmt::Display for DeadlockReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "probable deadlock:")?;
+        for (index, edge) in self.cycle.iter().enumerate() {
+            let holder = self.cycle[(index + 1) % self.cycle.len()].task;
+            write!(f, " task {} waits for lock {:#x}", edge.task, edge.lock)?;
+            if let Some(name) = edge.name {
+                write!(f, " ({})", name)?;
+            }
+            write!(f, " held by task {};", holder)?;
+        }
+        Ok(())
+    }
+}
+
+pub type DeadlockReporter = fn(&DeadlockReport);
+
+static ENABLED: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+static REPORTER: AtomicUsize = AtomicUsize::new(0);
+static DETECTED: AtomicU64 = AtomicU64::new(0);
+
+struct Graph {
+    /// Holders of each lock, a task once per read guard
+    holders: BTreeMap<usize, Vec<u64>>,
+    /// Lock each waiting task waits for
+    waiting: BTreeMap<u64, WaitEdge>,
+}
+
+static GRAPH: SpinLock<Graph> = SpinLock::new(Graph { holders: BTreeMap::new(), waiting: BTreeMap::new() });
+
+pub fn set_deadlock_detection(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn set_deadlock_reporter(reporter: Option<DeadlockReporter>) {
+    REPORTER.store(reporter.map_or(0, |reporter| reporter as usize), Ordering::Release);
+}
+
+/// Cycles found so far
+pub fn detected() -> u64 {
+    DETECTED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn acquired(task: u64, lock: usize) {
+    let mut graph = GRAPH.lock();
+    graph.waiting.remove(&task);
+    graph.holders.entry(lock).or_default().push(task);
+}
+
+pub(crate) fn released(task: u64, lock: usize) {
+    let mut graph = GRAPH.lock();
+    if let Some(holders) = graph.holders.get_mut(&lock) {
+        if let Some(position) = holders.iter().position(|&holder| holder == task) {
+            holders.swap_remove(position);
+        }
+        if holders.is_empty() {
+            graph.holders.remove(&lock);
+        }
+    }
+}
+
+pub(crate) fn stop_waiting(task: u64) {
+    GRAPH.lock().waiting.remove(&task);
+}
+
+/// Record that `edge.task` waits, and report the cycle it closes if any
+pub(crate) fn wait(edge: WaitEdge) {
+    let report = {
+        let mut graph = GRAPH.lock();
+        graph.waiting.insert(edge.task, edge);
+        let mut path = Vec::new();
+        let mut visited = BTreeSet::new();
+        find_cycle(&graph, edge.task, edge.task, &mut path, &mut visited).then_some(DeadlockReport { cycle: path })
+    };
+    if let Some(report) = report {
+        DETECTED.fetch_add(1, Ordering::Relaxed);
+        let reporter = REPORTER.load(Ordering::Acquire);
+        if reporter != 0 {
+            let reporter: DeadlockReporter = unsafe { core::mem::transmute(reporter) };
+            reporter(&report);
+        }
+    }
+}
+
+/// Depth-first search along waits-for edges from `task` back to `start`
+fn find_cycle(graph: &Graph, start: u64, task: u64, path: &mut Vec<WaitEdge>, visited: &mut BTreeSet<u64>) -> bool {
+    let Some(&edge) = graph.waiting.get(&task) else {
+        return false;
+    };
+    if !visited.insert(task) {
+        return false;
+    }
+    path.push(edge);
+    for &holder in graph.holders.get(&edge.lock).into_iter().flatten() {
+        if holder == start || find_cycle(graph, start, holder, path, visited) {
+            return true;
+        }
+    }
+    path.pop();
+    false
+}
@@ -0,0 +1,495 @@
+/*
+ * Orion Operating System - Async Executor
+ *
+ * Tasks are polled on the thread running the executor, so they need not
+ * be Send; their wakers are, and may be used from any thread. A woken
+ * task joins the ready queue of its priority. The highest priority goes
+ * first, but once higher priorities have been served STARVATION_LIMIT
+ * times in a row while a lower one waited, the oldest task of the next
+ * lower ready priority runs, so a busy high priority task slows the
+ * others down without stopping them. Each poll runs on a budget, see
+ * budget.rs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::budget;
+use crate::lock::SpinLock;
+use crate::timer::{self, TimerKey, TimerWheel};
+
+/// Higher priorities served in a row before a waiting lower one runs
+pub const STARVATION_LIMIT: u32 = 16;
+
+const NUM_PRIORITIES: usize = 3;
+
+/// Task id of the future given to `block_on`
+const MAIN_TASK: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High = 0,
+    Normal = 1,
+    Low = 2,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutorStats {
+    pub spawned: u64,
+    pub completed: u64,
+    pub polls: u64,
+    /// Polls ended by the task spending its budget
+    pub budget_yields: u64,
+    /// Lower priority tasks run ahead of higher ones to avoid starvation
+    pub starvation_boosts: u64,
+    pub timers_fired: u64,
+}
+
+/// Ready queues, reached by wakers from any thread
+struct Shared {
+    ready: SpinLock<[VecDeque<u64>; NUM_PRIORITIES]>,
+}
+
+struct TaskWaker {
+    id: u64,
+    priority: Priority,
+    /// Set while the task sits in a ready queue
+    queued: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            self.shared.ready.lock()[self.priority as usize].push_back(self.id);
+        }
+    }
+}
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    waker: Arc<TaskWaker>,
+}
+
+pub struct Executor {
+    shared: Arc<Shared>,
+    tasks: RefCell<BTreeMap<u64, Task>>,
+    timers: RefCell<TimerWheel>,
+    next_id: Cell<u64>,
+    /// Higher priority tasks run in a row while a lower one was ready
+    in_row: Cell<u32>,
+    stats: Cell<ExecutorStats>,
+}
+
+static CURRENT: AtomicPtr<Executor> = AtomicPtr::new(ptr::null_mut());
+
+const NO_TASK: u64 = u64::MAX - 1;
+
+/// Task being polled
+static CURRENT_TASK: AtomicU64 = AtomicU64::new(NO_TASK);
+
+/// Id of the task being polled, `block_on`'s future included; None
+/// outside any task
+pub fn current_task() -> Option<u64> {
+    match CURRENT_TASK.load(Ordering::Relaxed) {
+        NO_TASK => None,
+        task => Some(task),
+    }
+}
+
+/// Poll as task `id` with a fresh budget; true when the budget was spent
+fn poll_as<R>(id: u64, poll: impl FnOnce() -> R) -> (R, bool) {
+    let task = CURRENT_TASK.swap(id, Ordering::Relaxed);
+    let saved = budget::start();
+    let result = poll();
+    let exhausted = budget::finish(saved);
+    CURRENT_TASK.store(task, Ordering::Relaxed);
+    (result, exhausted)
+}
+
+/// Makes an executor current while it runs, restoring the previous one
+struct Enter {
+    previous: *mut Executor,
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        CURRENT.store(self.previous, Ordering::Release);
+    }
+}
+
+fn with_current<R>(f: impl FnOnce(&Executor) -> R) -> Option<R> {
+    let current = CURRENT.load(Ordering::Acquire);
+    // The pointer is only set while the executor runs, on this thread
+    unsafe { current.as_ref() }.map(f)
+}
+
+pub(crate) fn register_timer(previous: Option<TimerKey>, deadline: u64, waker: Waker) -> TimerKey {
+    with_current(|executor| {
+        let mut timers = executor.timers.borrow_mut();
+        if let Some(previous) = previous {
+            timers.cancel(previous);
+        }
+        timers.insert(deadline, waker)
+    })
+    .expect("timer used outside an executor")
+}
+
+pub(crate) fn cancel_timer(key: TimerKey) {
+    with_current(|executor| executor.timers.borrow_mut().cancel(key));
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared { ready: SpinLock::new([const { VecDeque::new() }; NUM_PRIORITIES]) }),
+            tasks: RefCell::new(BTreeMap::new()),
+            timers: RefCell::new(TimerWheel::new()),
+            next_id: Cell::new(0),
+            in_row: Cell::new(0),
+            stats: Cell::new(ExecutorStats::default()),
+        }
+    }
+
+    pub fn stats(&self) -> ExecutorStats {
+        self.stats.get()
+    }
+
+    /// Tasks spawned and not completed yet
+    pub fn tasks(&self) -> usize {
+        self.tasks.borrow().len()
+    }
+
+    /// Sleeping tasks waiting on the wheel
+    pub fn timers(&self) -> usize {
+        self.timers.borrow().len()
+    }
+
+    fn update_stats(&self, update: impl FnOnce(&mut ExecutorStats)) {
+        let mut stats = self.stats.get();
+        update(&mut stats);
+        self.stats.set(stats);
+    }
+
+    fn enter(&self) -> Enter {
+        Enter { previous: CURRENT.swap(self as *const Executor as *mut Executor, Ordering::AcqRel) }
+    }
+
+    fn waker(&self, id: u64, priority: Priority) -> Arc<TaskWaker> {
+        Arc::new(TaskWaker { id, priority, queued: AtomicBool::new(false), shared: self.shared.clone() })
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        self.spawn_with_priority(Priority::Normal, future)
+    }
+
+    pub fn spawn_with_priority<F>(&self, priority: Priority, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let state = Rc::new(RefCell::new(JoinState { output: None, waker: None }));
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        let joined = state.clone();
+        let future = Box::pin(async move {
+            let output = future.await;
+            let mut state = joined.borrow_mut();
+            state.output = Some(output);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        let waker = self.waker(id, priority);
+        waker.wake_by_ref();
+        self.tasks.borrow_mut().insert(id, Task { future, waker });
+        self.update_stats(|stats| stats.spawned += 1);
+        JoinHandle { state }
+    }
+
+    fn fire_timers(&self) {
+        let mut fired = Vec::new();
+        self.timers.borrow_mut().advance(timer::now(), &mut fired);
+        if !fired.is_empty() {
+            self.update_stats(|stats| stats.timers_fired += fired.len() as u64);
+        }
+        for waker in fired {
+            waker.wake();
+        }
+    }
+
+    fn next_ready(&self) -> Option<u64> {
+        let mut ready = self.shared.ready.lock();
+        let highest = ready.iter().position(|queue| !queue.is_empty())?;
+        let lower = (highest + 1..NUM_PRIORITIES).find(|&priority| !ready[priority].is_empty());
+        match lower {
+            Some(lower) if self.in_row.get() >= STARVATION_LIMIT => {
+                self.in_row.set(0);
+                self.update_stats(|stats| stats.starvation_boosts += 1);
+                ready[lower].pop_front()
+            }
+            Some(_) => {
+                self.in_row.set(self.in_row.get() + 1);
+                ready[highest].pop_front()
+            }
+            None => {
+                self.in_row.set(0);
+                ready[highest].pop_front()
+            }
+        }
+    }
+
+    fn poll_task(&self, id: u64) {
+        // Taken out of the table while polled, so that it may spawn
+        let Some(mut task) = self.tasks.borrow_mut().remove(&id) else {
+            return;
+        };
+        task.waker.queued.store(false, Ordering::Release);
+        let waker = Waker::from(task.waker.clone());
+        let (poll, exhausted) = poll_as(id, || task.future.as_mut().poll(&mut Context::from_waker(&waker)));
+        self.update_stats(|stats| {
+            stats.polls += 1;
+            stats.budget_yields += exhausted as u64;
+            stats.completed += poll.is_ready() as u64;
+        });
+        if poll.is_pending() {
+            self.tasks.borrow_mut().insert(id, task);
+        }
+    }
+
+    fn idle(&self) {
+        let deadline = self.timers.borrow().next_deadline();
+        timer::idle(deadline.map(|deadline| deadline.saturating_sub(timer::now())));
+    }
+
+    /// Run `future` to completion, along with the spawned tasks
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _enter = self.enter();
+        let mut future = pin!(future);
+        let main = self.waker(MAIN_TASK, Priority::Normal);
+        main.wake_by_ref();
+        loop {
+            self.fire_timers();
+            match self.next_ready() {
+                Some(MAIN_TASK) => {
+                    main.queued.store(false, Ordering::Release);
+                    let waker = Waker::from(main.clone());
+                    let (poll, exhausted) =
+                        poll_as(MAIN_TASK, || future.as_mut().poll(&mut Context::from_waker(&waker)));
+                    self.update_stats(|stats| {
+                        stats.polls += 1;
+                        stats.budget_yields += exhausted as u64;
+                    });
+                    if let Poll::Ready(output) = poll {
+                        return output;
+                    }
+                }
+                Some(id) => self.poll_task(id),
+                None => self.idle(),
+            }
+        }
+    }
+
+    /// Run spawned tasks until none is left
+    pub fn run(&self) {
+        let _enter = self.enter();
+        while !self.tasks.borrow().is_empty() {
+            self.fire_timers();
+            match self.next_ready() {
+                Some(id) => self.poll_task(id),
+                None => self.idle(),
+            }
+        }
+    }
+}
+
+/// Run `future` to completion on a new executor
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Executor::new().block_on(future)
+}
+
+/// Spawn a task on the running executor
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    with_current(|executor| executor.spawn(future)).expect("spawn outside an executor")
+}
+
+pub fn spawn_with_priority<F>(priority: Priority, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    with_current(|executor| executor.spawn_with_priority(priority, future)).expect("spawn outside an executor")
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Output of a spawned task; dropping it leaves the task running
+pub struct JoinHandle<T> {
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().output.is_some()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Let the other ready tasks run before continuing
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::AsyncChannel;
+    use alloc::vec;
+
+    #[test]
+    fn test_higher_priorities_run_first_and_join_handles_complete() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let handles: Vec<_> = [(Priority::Low, 3), (Priority::Normal, 2), (Priority::High, 1)]
+            .into_iter()
+            .map(|(priority, value)| {
+                let order = order.clone();
+                executor.spawn_with_priority(priority, async move {
+                    order.borrow_mut().push(priority);
+                    value * 10
+                })
+            })
+            .collect();
+        executor.run();
+        assert_eq!(*order.borrow(), [Priority::High, Priority::Normal, Priority::Low]);
+        assert!(handles.iter().all(JoinHandle::is_finished));
+
+        let total = executor.block_on(async move {
+            let mut total = 0;
+            for handle in handles {
+                total += handle.await;
+            }
+            total + spawn(async { 4 }).await
+        });
+        assert_eq!(total, 64);
+        assert_eq!(executor.stats().completed, 4);
+    }
+
+    #[test]
+    fn test_busy_high_priority_task_does_not_starve_low_ones() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let done = Rc::new(Cell::new(false));
+        let spins = Rc::new(Cell::new(0u32));
+        {
+            let (done, spins) = (done.clone(), spins.clone());
+            executor.spawn_with_priority(Priority::High, async move {
+                while !done.get() {
+                    spins.set(spins.get() + 1);
+                    yield_now().await;
+                }
+            });
+        }
+        {
+            let done = done.clone();
+            executor.spawn_with_priority(Priority::Low, async move { done.set(true) });
+        }
+        executor.run();
+        assert_eq!(spins.get(), STARVATION_LIMIT);
+        assert_eq!(executor.stats().starvation_boosts, 1);
+    }
+
+    #[test]
+    fn test_budget_makes_always_ready_loops_yield() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let channel = Rc::new(AsyncChannel::new(1024));
+        for value in 0..1000 {
+            channel.try_send(value).unwrap();
+        }
+        channel.close();
+        let other_ran_at = Rc::new(Cell::new(None));
+        {
+            let receiver = channel.clone();
+            executor.spawn(async move {
+                // Only the budget stops this loop before the channel is empty
+                let mut received = vec![];
+                while let Ok(value) = receiver.recv().await {
+                    received.push(value);
+                }
+                assert_eq!(received.len(), 1000);
+            });
+            let (channel, other_ran_at) = (channel.clone(), other_ran_at.clone());
+            executor.spawn(async move { other_ran_at.set(Some(channel.len())) });
+        }
+        executor.run();
+        assert_eq!(other_ran_at.get(), Some(1000 - budget::TASK_BUDGET as usize));
+        assert!(executor.stats().budget_yields >= 1000 / budget::TASK_BUDGET as u64);
+    }
+}
@@ -0,0 +1,56 @@
+/*
+ * Orion Operating System - Async Runtime
+ *
+ * Executor and primitives for drivers and servers written as futures.
+ * Tasks are spawned at one of three priorities and polled on the thread
+ * running the executor; a timer wheel driven by the monotonic clock
+ * wakes sleeping tasks, and every poll runs on a budget so that a task
+ * finding its channel or lock always ready still gives way to others.
+ * Rare blocking operations are handed to a helper thread or service
+ * and awaited like any other future. The locks serve their waiters in
+ * order and, in debug builds, report tasks waiting on each other.
+ *
+ *     orion_async::set_clock(|| clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0));
+ *     orion_async::set_idle(|ns| { let _ = nanosleep(ns); });
+ *     let result = orion_async::block_on(driver.initialize());
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod blocking;
+pub mod budget;
+pub mod channel;
+pub mod deadlock;
+pub mod executor;
+pub mod sync;
+pub mod timer;
+
+mod lock;
+
+pub use core::future::Future;
+pub use core::pin::Pin;
+pub use core::task::{Context, Poll, Waker};
+
+pub use blocking::{set_blocking_backend, spawn_blocking, Blocking, BlockingBackend, HelperQueue, Job};
+pub use channel::{AsyncChannel, RecvError, SendError};
+pub use deadlock::{set_deadlock_detection, set_deadlock_reporter, DeadlockReport, DeadlockReporter, WaitEdge};
+pub use executor::{
+    block_on, current_task, spawn, spawn_with_priority, yield_now, Executor, ExecutorStats, JoinHandle, Priority,
+};
+pub use sync::{AsyncMutex, AsyncMutexGuard, AsyncRwLock, AsyncRwLockReadGuard, AsyncRwLockWriteGuard};
+pub use timer::{now, set_clock, set_idle, sleep, sleep_until, timeout, Elapsed, Sleep, Timeout, TICK_NS};
+
+/// Tests share the current executor, the budget and the virtual clock
+#[cfg(test)]
+pub(crate) fn serial() -> lock::SpinGuard<'static, ()> {
+    static SERIAL: lock::SpinLock<()> = lock::SpinLock::new(());
+    SERIAL.lock()
+}
@@ -0,0 +1,558 @@
+/*
+ * Orion Operating System - Async Locks
+ *
+ * Mutex and reader-writer lock whose waiters are tasks rather than
+ * threads. Waiters queue in arrival order and the lock is handed over
+ * on release: to the waiter at the front, or to every reader at the
+ * front up to the first writer. A task arriving while others wait
+ * queues behind them even if the lock is momentarily free, so neither
+ * writers nor late readers can be overtaken forever. A waiter giving up,
+ * through a timeout or by being dropped, leaves the queue, passing on a
+ * lock it had already been handed. Acquiring takes a unit of the task's
+ * budget; waits and holders feed the deadlock diagnostics when enabled.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use crate::budget;
+use crate::deadlock::{self, WaitEdge};
+use crate::executor::current_task;
+use crate::lock::SpinLock;
+use crate::timer::{timeout, Timeout};
+
+struct Waiter {
+    ticket: u64,
+    write: bool,
+    waker: Waker,
+}
+
+struct LockState {
+    /// Readers holding the lock, or WRITER
+    holders: usize,
+    waiters: VecDeque<Waiter>,
+    /// Waiters handed the lock and not polled since
+    granted: Vec<u64>,
+    next_ticket: u64,
+}
+
+const WRITER: usize = usize::MAX;
+
+impl LockState {
+    const fn new() -> Self {
+        Self { holders: 0, waiters: VecDeque::new(), granted: Vec::new(), next_ticket: 0 }
+    }
+
+    fn is_free(&self, write: bool) -> bool {
+        if write {
+            self.holders == 0
+        } else {
+            self.holders != WRITER
+        }
+    }
+
+    fn take(&mut self, write: bool) {
+        self.holders = if write { WRITER } else { self.holders + 1 };
+    }
+
+    /// Take the lock without waiting, unless others wait already
+    fn try_take(&mut self, write: bool) -> bool {
+        let free = self.waiters.is_empty() && self.is_free(write);
+        if free {
+            self.take(write);
+        }
+        free
+    }
+
+    /// Acquire for the waiter holding `ticket`, None until it queues
+    fn poll_acquire(&mut self, ticket: &mut Option<u64>, write: bool, waker: &Waker) -> Poll<()> {
+        if let Some(id) = *ticket {
+            if let Some(position) = self.granted.iter().position(|&granted| granted == id) {
+                self.granted.swap_remove(position);
+                *ticket = None;
+                return Poll::Ready(());
+            }
+            if let Some(waiter) = self.waiters.iter_mut().find(|waiter| waiter.ticket == id) {
+                waiter.waker.clone_from(waker);
+            }
+            return Poll::Pending;
+        }
+        if self.try_take(write) {
+            return Poll::Ready(());
+        }
+        let id = self.next_ticket;
+        self.next_ticket += 1;
+        self.waiters.push_back(Waiter { ticket: id, write, waker: waker.clone() });
+        *ticket = Some(id);
+        Poll::Pending
+    }
+
+    /// Hand the lock to the waiters at the front; their wakers
+    fn grant(&mut self) -> Vec<Waker> {
+        let mut woken = Vec::new();
+        while let Some(waiter) = self.waiters.front() {
+            if !self.is_free(waiter.write) {
+                break;
+            }
+            let waiter = self.waiters.pop_front().unwrap();
+            self.take(waiter.write);
+            self.granted.push(waiter.ticket);
+            woken.push(waiter.waker);
+            if waiter.write {
+                break;
+            }
+        }
+        woken
+    }
+
+    fn release(&mut self) -> Vec<Waker> {
+        self.holders = if self.holders == WRITER { 0 } else { self.holders - 1 };
+        self.grant()
+    }
+
+    /// The waiter holding `ticket` gave up
+    fn cancel(&mut self, ticket: u64) -> Vec<Waker> {
+        if let Some(position) = self.waiters.iter().position(|waiter| waiter.ticket == ticket) {
+            self.waiters.remove(position);
+            // Readers queued behind a departing writer may go
+            return self.grant();
+        }
+        match self.granted.iter().position(|&granted| granted == ticket) {
+            Some(position) => {
+                self.granted.swap_remove(position);
+                self.release()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+fn wake_all(waiters: Vec<Waker>) {
+    for waiter in waiters {
+        waiter.wake();
+    }
+}
+
+/// Place of a waiting future in the queue of its lock
+#[derive(Default)]
+struct Ticket {
+    id: Option<u64>,
+    /// Task recorded as waiting, when diagnostics are on
+    task: Option<u64>,
+}
+
+/// Lock shared by the mutex and the reader-writer lock
+struct RawLock {
+    state: SpinLock<LockState>,
+    name: Option<&'static str>,
+}
+
+impl RawLock {
+    const fn new(name: Option<&'static str>) -> Self {
+        Self { state: SpinLock::new(LockState::new()), name }
+    }
+
+    fn address(&self) -> usize {
+        self as *const RawLock as usize
+    }
+
+    /// Task to record as holder, when diagnostics are on
+    fn holder(&self) -> Option<u64> {
+        let task = current_task().filter(|_| deadlock::enabled())?;
+        deadlock::acquired(task, self.address());
+        Some(task)
+    }
+
+    fn try_acquire(&self, write: bool) -> Option<Option<u64>> {
+        self.state.lock().try_take(write).then(|| self.holder())
+    }
+
+    /// Some(holder) once acquired
+    fn poll_acquire(&self, ticket: &mut Ticket, write: bool, cx: &mut Context<'_>) -> Poll<Option<u64>> {
+        if budget::poll_proceed(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let queued = ticket.id.is_some();
+        if self.state.lock().poll_acquire(&mut ticket.id, write, cx.waker()).is_ready() {
+            ticket.task = None;
+            return Poll::Ready(self.holder());
+        }
+        if !queued && deadlock::enabled() {
+            if let Some(task) = current_task() {
+                ticket.task = Some(task);
+                deadlock::wait(WaitEdge { task, lock: self.address(), name: self.name });
+            }
+        }
+        Poll::Pending
+    }
+
+    fn cancel(&self, ticket: &Ticket) {
+        if let Some(task) = ticket.task {
+            deadlock::stop_waiting(task);
+        }
+        if let Some(id) = ticket.id {
+            let woken = self.state.lock().cancel(id);
+            wake_all(woken);
+        }
+    }
+
+    fn release(&self, holder: Option<u64>) {
+        if let Some(holder) = holder {
+            deadlock::released(holder, self.address());
+        }
+        let woken = self.state.lock().release();
+        wake_all(woken);
+    }
+}
+
+pub struct AsyncMutex<T: ?Sized> {
+    raw: RawLock,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+
+impl<T> AsyncMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self { raw: RawLock::new(None), value: UnsafeCell::new(value) }
+    }
+
+    /// Mutex called `name` in deadlock reports
+    pub const fn named(name: &'static str, value: T) -> Self {
+        Self { raw: RawLock::new(Some(name)), value: UnsafeCell::new(value) }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+    pub fn lock(&self) -> MutexLock<'_, T> {
+        MutexLock { mutex: self, ticket: Ticket::default() }
+    }
+
+    /// Lock, giving up after `duration` nanoseconds
+    pub fn lock_timeout(&self, duration: u64) -> Timeout<MutexLock<'_, T>> {
+        timeout(duration, self.lock())
+    }
+
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+        let holder = self.raw.try_acquire(true)?;
+        Some(AsyncMutexGuard { mutex: self, holder })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+pub struct MutexLock<'a, T: ?Sized> {
+    mutex: &'a AsyncMutex<T>,
+    ticket: Ticket,
+}
+
+impl<'a, T: ?Sized> Future for MutexLock<'a, T> {
+    type Output = AsyncMutexGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mutex = this.mutex;
+        mutex.raw.poll_acquire(&mut this.ticket, true, cx).map(|holder| AsyncMutexGuard { mutex, holder })
+    }
+}
+
+impl<T: ?Sized> Drop for MutexLock<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.raw.cancel(&self.ticket);
+    }
+}
+
+pub struct AsyncMutexGuard<'a, T: ?Sized> {
+    mutex: &'a AsyncMutex<T>,
+    holder: Option<u64>,
+}
+
+impl<T: ?Sized> Deref for AsyncMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.raw.release(self.holder);
+    }
+}
+
+pub struct AsyncRwLock<T: ?Sized> {
+    raw: RawLock,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncRwLock<T> {}
+
+impl<T> AsyncRwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self { raw: RawLock::new(None), value: UnsafeCell::new(value) }
+    }
+
+    /// Lock called `name` in deadlock reports
+    pub const fn named(name: &'static str, value: T) -> Self {
+        Self { raw: RawLock::new(Some(name)), value: UnsafeCell::new(value) }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AsyncRwLock<T> {
+    pub fn read(&self) -> RwLockAcquire<'_, T, false> {
+        RwLockAcquire { lock: self, ticket: Ticket::default() }
+    }
+
+    pub fn write(&self) -> RwLockAcquire<'_, T, true> {
+        RwLockAcquire { lock: self, ticket: Ticket::default() }
+    }
+
+    /// Lock for reading, giving up after `duration` nanoseconds
+    pub fn read_timeout(&self, duration: u64) -> Timeout<RwLockAcquire<'_, T, false>> {
+        timeout(duration, self.read())
+    }
+
+    /// Lock for writing, giving up after `duration` nanoseconds
+    pub fn write_timeout(&self, duration: u64) -> Timeout<RwLockAcquire<'_, T, true>> {
+        timeout(duration, self.write())
+    }
+
+    pub fn try_read(&self) -> Option<AsyncRwLockReadGuard<'_, T>> {
+        let holder = self.raw.try_acquire(false)?;
+        Some(AsyncRwLockReadGuard { lock: self, holder })
+    }
+
+    pub fn try_write(&self) -> Option<AsyncRwLockWriteGuard<'_, T>> {
+        let holder = self.raw.try_acquire(true)?;
+        Some(AsyncRwLockWriteGuard { lock: self, holder })
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+pub struct RwLockAcquire<'a, T: ?Sized, const WRITE: bool> {
+    lock: &'a AsyncRwLock<T>,
+    ticket: Ticket,
+}
+
+impl<'a, T: ?Sized> Future for RwLockAcquire<'a, T, false> {
+    type Output = AsyncRwLockReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let lock = this.lock;
+        lock.raw.poll_acquire(&mut this.ticket, false, cx).map(|holder| AsyncRwLockReadGuard { lock, holder })
+    }
+}
+
+impl<'a, T: ?Sized> Future for RwLockAcquire<'a, T, true> {
+    type Output = AsyncRwLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let lock = this.lock;
+        lock.raw.poll_acquire(&mut this.ticket, true, cx).map(|holder| AsyncRwLockWriteGuard { lock, holder })
+    }
+}
+
+impl<T: ?Sized, const WRITE: bool> Drop for RwLockAcquire<'_, T, WRITE> {
+    fn drop(&mut self) {
+        self.lock.raw.cancel(&self.ticket);
+    }
+}
+
+pub struct AsyncRwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+    holder: Option<u64>,
+}
+
+impl<T: ?Sized> Deref for AsyncRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.raw.release(self.holder);
+    }
+}
+
+pub struct AsyncRwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+    holder: Option<u64>,
+}
+
+impl<T: ?Sized> Deref for AsyncRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.raw.release(self.holder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deadlock::{self, DeadlockReport};
+    use crate::executor::{spawn, yield_now, Executor};
+    use crate::timer::{sleep, Elapsed, TICK_NS};
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_tasks_take_turns_on_locks() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let counter = Rc::new(AsyncMutex::new(0u32));
+        let table = Rc::new(AsyncRwLock::new([0u32; 4]));
+        for task in 0..4 {
+            let (counter, table) = (counter.clone(), table.clone());
+            executor.spawn(async move {
+                for _ in 0..10 {
+                    // Held across a yield, so the others have to wait
+                    let mut value = counter.lock().await;
+                    let seen = *value;
+                    yield_now().await;
+                    *value = seen + 1;
+                }
+                table.write().await[task] = 10;
+                let readers = (table.read().await, table.read().await);
+                assert!(table.try_write().is_none() && table.try_read().is_some());
+                drop(readers);
+            });
+        }
+        executor.run();
+        assert_eq!(*counter.try_lock().unwrap(), 40);
+        assert_eq!(executor.block_on(async { *table.read().await }), [10; 4]);
+    }
+    #[test]
+    fn test_waiters_are_served_in_arrival_order_and_may_time_out() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let lock = Rc::new(AsyncRwLock::new(0));
+        let order = Rc::new(RefCell::new(Vec::new()));
+        executor.block_on(async {
+            let held = lock.read().await;
+            for (name, write) in [("writer", true), ("late reader", false), ("reader", false)] {
+                let (lock, order) = (lock.clone(), order.clone());
+                spawn(async move {
+                    if write {
+                        let _guard = lock.write().await;
+                        order.borrow_mut().push(name);
+                    } else {
+                        let _guard = lock.read().await;
+                        order.borrow_mut().push(name);
+                    }
+                });
+                yield_now().await;
+            }
+            // The lock is free for readers, but a writer waits ahead of them
+            assert!(lock.try_read().is_none());
+            assert_eq!(lock.write_timeout(10 * TICK_NS).await.err(), Some(Elapsed));
+            drop(held);
+            sleep(TICK_NS).await;
+        });
+        assert_eq!(*order.borrow(), ["writer", "late reader", "reader"]);
+
+        // A waiter timing out passes on its place
+        let mutex = Rc::new(AsyncMutex::new(()));
+        let acquired = executor.block_on(async {
+            let held = mutex.lock().await;
+            let waiter = {
+                let mutex = mutex.clone();
+                spawn(async move { drop(mutex.lock().await) })
+            };
+            assert!(mutex.lock_timeout(TICK_NS).await.is_err());
+            drop(held);
+            waiter.await;
+            mutex.try_lock().is_some()
+        });
+        assert!(acquired);
+    }
+
+    static REPORTS: SpinLock<Vec<DeadlockReport>> = SpinLock::new(Vec::new());
+
+    fn record(report: &DeadlockReport) {
+        REPORTS.lock().push(report.clone());
+    }
+
+    #[test]
+    fn test_lock_order_inversion_is_reported() {
+        let _serial = crate::serial();
+        deadlock::set_deadlock_detection(true);
+        deadlock::set_deadlock_reporter(Some(record));
+        let executor = Executor::new();
+        let first = Rc::new(AsyncMutex::named("first", ()));
+        let second = Rc::new(AsyncMutex::named("second", ()));
+        executor.block_on(async {
+            for swap in [false, true] {
+                let (mut a, mut b) = (first.clone(), second.clone());
+                if swap {
+                    core::mem::swap(&mut a, &mut b);
+                }
+                spawn(async move {
+                    let _a = a.lock().await;
+                    yield_now().await;
+                    let _b = b.lock().await;
+                });
+            }
+            sleep(TICK_NS).await;
+        });
+        deadlock::set_deadlock_reporter(None);
+        drop(executor);
+
+        let reports = core::mem::take(&mut *REPORTS.lock());
+        assert_eq!(reports.len(), 1);
+        // The second task closed the cycle, waiting for the first lock
+        let edges: Vec<_> = reports[0].cycle.iter().map(|edge| (edge.task, edge.name)).collect();
+        assert_eq!(edges, [(1, Some("first")), (0, Some("second"))]);
+        assert!(deadlock::detected() >= 1);
+    }
+}
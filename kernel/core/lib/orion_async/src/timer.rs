@@ -0,0 +1,291 @@
+/*
+ * Orion Operating System - Async Timers
+ *
+ * Sleeping tasks wait on a wheel of millisecond slots; a deadline lands
+ * in the slot of its tick, and entries further away than one turn stay
+ * in their slot until the wheel comes round to their deadline. The
+ * executor advances the wheel from the clock before picking a task, and
+ * when idle sleeps until the earliest deadline.
+ *
+ * Time is read from the clock the program installs, normally the
+ * monotonic clock of the time subsystem. Without one, time is virtual:
+ * it stands still while tasks run and jumps to the next deadline when
+ * the executor is idle.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::executor;
+
+/// Resolution of the wheel
+pub const TICK_NS: u64 = 1_000_000;
+
+const WHEEL_SLOTS: usize = 256;
+
+/// Longest idle sleep, so that tasks woken from other threads are not
+/// left waiting for a distant deadline
+const MAX_IDLE_NS: u64 = 10 * TICK_NS;
+
+static CLOCK: AtomicUsize = AtomicUsize::new(0);
+static IDLE: AtomicUsize = AtomicUsize::new(0);
+static VIRTUAL_NOW: AtomicU64 = AtomicU64::new(0);
+
+/// Install the clock, in nanoseconds, timers are driven by
+pub fn set_clock(clock: fn() -> u64) {
+    CLOCK.store(clock as usize, Ordering::Relaxed);
+}
+
+/// Install the function putting the thread to sleep for some nanoseconds
+/// when no task is ready; the executor spins without one
+pub fn set_idle(idle: fn(u64)) {
+    IDLE.store(idle as usize, Ordering::Relaxed);
+}
+
+/// Current time in nanoseconds
+pub fn now() -> u64 {
+    match CLOCK.load(Ordering::Relaxed) {
+        0 => VIRTUAL_NOW.load(Ordering::Relaxed),
+        clock => {
+            let clock: fn() -> u64 = unsafe { core::mem::transmute(clock) };
+            clock()
+        }
+    }
+}
+
+/// Wait for `duration` nanoseconds at most, or until woken
+pub(crate) fn idle(duration: Option<u64>) {
+    if CLOCK.load(Ordering::Relaxed) == 0 {
+        match duration {
+            Some(duration) => {
+                VIRTUAL_NOW.fetch_add(duration, Ordering::Relaxed);
+            }
+            None => core::hint::spin_loop(),
+        }
+        return;
+    }
+    match IDLE.load(Ordering::Relaxed) {
+        0 => core::hint::spin_loop(),
+        idle => {
+            let idle: fn(u64) = unsafe { core::mem::transmute(idle) };
+            idle(duration.unwrap_or(MAX_IDLE_NS).min(MAX_IDLE_NS));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimerKey {
+    id: u64,
+    slot: usize,
+}
+
+struct Entry {
+    id: u64,
+    deadline: u64,
+    waker: Waker,
+}
+
+pub(crate) struct TimerWheel {
+    slots: Vec<Vec<Entry>>,
+    /// Tick the wheel was last advanced to
+    tick: u64,
+    next_id: u64,
+    len: usize,
+}
+
+impl TimerWheel {
+    pub fn new() -> Self {
+        Self { slots: (0..WHEEL_SLOTS).map(|_| Vec::new()).collect(), tick: now() / TICK_NS, next_id: 0, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn insert(&mut self, deadline: u64, waker: Waker) -> TimerKey {
+        let tick = (deadline / TICK_NS).max(self.tick);
+        let slot = tick as usize % WHEEL_SLOTS;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.slots[slot].push(Entry { id, deadline, waker });
+        self.len += 1;
+        TimerKey { id, slot }
+    }
+
+    pub fn cancel(&mut self, key: TimerKey) {
+        let entries = &mut self.slots[key.slot];
+        if let Some(position) = entries.iter().position(|entry| entry.id == key.id) {
+            entries.swap_remove(position);
+            self.len -= 1;
+        }
+    }
+
+    /// Take the wakers of every timer due at `now`
+    pub fn advance(&mut self, now: u64, fired: &mut Vec<Waker>) {
+        let now_tick = now / TICK_NS;
+        if now_tick < self.tick {
+            return;
+        }
+        if self.len != 0 {
+            let span = (now_tick - self.tick + 1).min(WHEEL_SLOTS as u64);
+            for offset in 0..span {
+                let entries = &mut self.slots[(self.tick + offset) as usize % WHEEL_SLOTS];
+                let mut index = 0;
+                while index < entries.len() {
+                    if entries[index].deadline <= now {
+                        fired.push(entries.swap_remove(index).waker);
+                        self.len -= 1;
+                    } else {
+                        index += 1;
+                    }
+                }
+            }
+        }
+        self.tick = now_tick;
+    }
+
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.slots.iter().flatten().map(|entry| entry.deadline).min()
+    }
+}
+
+/// Future completing at a deadline
+pub struct Sleep {
+    deadline: u64,
+    key: Option<TimerKey>,
+}
+
+/// Wait `duration` nanoseconds
+pub fn sleep(duration: u64) -> Sleep {
+    sleep_until(now().saturating_add(duration))
+}
+
+/// Wait until the clock reads `deadline`
+pub fn sleep_until(deadline: u64) -> Sleep {
+    Sleep { deadline, key: None }
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> u64 {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if now() >= self.deadline {
+            if let Some(key) = self.key.take() {
+                executor::cancel_timer(key);
+            }
+            return Poll::Ready(());
+        }
+        let key = executor::register_timer(self.key.take(), self.deadline, cx.waker().clone());
+        self.key = Some(key);
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            executor::cancel_timer(key);
+        }
+    }
+}
+
+/// The future given to `timeout` did not complete in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// Future giving up on another after a delay
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+/// Run `future` for `duration` nanoseconds at most
+pub fn timeout<F: Future>(duration: u64, future: F) -> Timeout<F> {
+    Timeout { future, sleep: sleep(duration) }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The future is never moved out of the pinned timeout
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Poll::Ready(output) = unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut this.sleep).poll(cx).map(|()| Err(Elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{spawn, Executor};
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_wheel_fires_due_timers_across_turns() {
+        let _serial = crate::serial();
+        let start = now();
+        let mut wheel = TimerWheel::new();
+        let waker = Waker::noop().clone();
+        let near = start + 3 * TICK_NS;
+        // A full turn and a bit away, sharing the slot of `near`
+        let far = start + (WHEEL_SLOTS as u64 + 3) * TICK_NS;
+        wheel.insert(near, waker.clone());
+        wheel.insert(far, waker.clone());
+        let cancelled = wheel.insert(start + 5 * TICK_NS, waker);
+        wheel.cancel(cancelled);
+        assert_eq!(wheel.next_deadline(), Some(near));
+
+        let mut fired = Vec::new();
+        wheel.advance(near, &mut fired);
+        assert_eq!((fired.len(), wheel.len()), (1, 1));
+        wheel.advance(far - 1, &mut fired);
+        assert_eq!(fired.len(), 1);
+        wheel.advance(far, &mut fired);
+        assert_eq!((fired.len(), wheel.len()), (2, 0));
+    }
+
+    #[test]
+    fn test_sleepers_wake_in_deadline_order_and_timeouts_elapse() {
+        let _serial = crate::serial();
+        let executor = Executor::new();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let start = now();
+        let result = executor.block_on({
+            let order = order.clone();
+            async move {
+                for (delay, name) in [(30, "c"), (10, "a"), (20, "b")] {
+                    let order = order.clone();
+                    spawn(async move {
+                        sleep(delay * TICK_NS).await;
+                        order.borrow_mut().push(name);
+                    });
+                }
+                sleep(40 * TICK_NS).await;
+                timeout(5 * TICK_NS, sleep(50 * TICK_NS)).await
+            }
+        });
+        assert_eq!(*order.borrow(), ["a", "b", "c"]);
+        assert_eq!(result, Err(Elapsed));
+        assert_eq!(now() - start, 45 * TICK_NS);
+        assert_eq!(executor.stats().timers_fired, 5);
+    }
+}
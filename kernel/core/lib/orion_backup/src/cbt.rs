@@ -0,0 +1,366 @@
+/*
+ * Orion Operating System - Changed-Block Tracking
+ *
+ * One bit per tracking block of a volume, set by every write, trim or
+ * resize touching it. Backup points are numbered by generation: taking
+ * one moves the current bitmap into the point and starts an empty one, so
+ * writes racing with the backup are picked up by the next one. A point
+ * that fails is merged back and nothing is lost.
+ *
+ * The tracker survives reboots through a small record the driver stores
+ * with the volume metadata. The record is written unclean as soon as the
+ * volume is opened and clean at orderly shutdown: a record still unclean
+ * at load time means writes may have gone unrecorded before a crash, and
+ * the next backup is forced to be a full one.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_crypto::sha512::Sha512;
+
+use crate::BackupError;
+
+const RECORD_MAGIC: &[u8; 4] = b"OCBT";
+const RECORD_VERSION: u32 = 1;
+const RECORD_HEADER_SIZE: usize = 40;
+const RECORD_DIGEST_SIZE: usize = 8;
+
+/// Record flag: written at orderly shutdown, nothing left unrecorded
+const RECORD_CLEAN: u32 = 1 << 0;
+
+pub const MIN_BLOCK_SIZE: u32 = 512;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    bits: u64,
+}
+
+impl Bitmap {
+    pub fn new(bits: u64) -> Self {
+        Self { words: vec![0; bits.div_ceil(64) as usize], bits }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.bits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn get(&self, bit: u64) -> bool {
+        bit < self.bits && self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+    }
+
+    pub fn set_range(&mut self, first: u64, count: u64) {
+        let end = first.saturating_add(count).min(self.bits);
+        for bit in first..end {
+            self.words[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn set_all(&mut self) {
+        self.set_range(0, self.bits);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.words.iter().map(|word| word.count_ones() as u64).sum()
+    }
+
+    fn merge(&mut self, other: &Bitmap) {
+        for (word, theirs) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= theirs;
+        }
+    }
+
+    /// Change the number of bits; bits added are set
+    fn resize(&mut self, bits: u64) {
+        let old = self.bits;
+        self.words.resize(bits.div_ceil(64) as usize, 0);
+        self.bits = bits;
+        if bits > old {
+            self.set_range(old, bits - old);
+        } else if !bits.is_multiple_of(64) {
+            // Keep bits past the end clear for count()
+            let last = self.words.len() - 1;
+            self.words[last] &= (1 << (bits % 64)) - 1;
+        }
+    }
+
+    /// First run of set bits at or after `from`, at most `max` long
+    pub fn next_run(&self, from: u64, max: u64) -> Option<(u64, u64)> {
+        let first = (from..self.bits).find(|&bit| self.get(bit))?;
+        let mut count = 1;
+        while count < max && self.get(first + count) {
+            count += 1;
+        }
+        Some((first, count))
+    }
+}
+
+/// Blocks changed between a backup and the one it follows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupPoint {
+    /// Generation of the backup this one is relative to; None for a full backup
+    pub base: Option<u64>,
+    pub generation: u64,
+    pub volume_size: u64,
+    pub block_size: u32,
+    pub changed: Bitmap,
+}
+
+impl BackupPoint {
+    pub fn changed_bytes(&self) -> u64 {
+        (self.changed.count() * self.block_size as u64).min(self.volume_size)
+    }
+}
+
+pub struct ChangeTracker {
+    volume_size: u64,
+    block_size: u32,
+    changed: Bitmap,
+    last_backup: Option<u64>,
+    next_generation: u64,
+    in_flight: Option<u64>,
+}
+
+fn record_digest(data: &[u8]) -> [u8; RECORD_DIGEST_SIZE] {
+    let mut digest = [0u8; RECORD_DIGEST_SIZE];
+    digest.copy_from_slice(&Sha512::digest(data)[..RECORD_DIGEST_SIZE]);
+    digest
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(raw)
+}
+
+impl ChangeTracker {
+    /// Tracker for a volume with no backup yet; `block_size` is the
+    /// tracking granularity, a power of two of at least 512 bytes
+    pub fn new(volume_size: u64, block_size: u32) -> Result<Self, BackupError> {
+        if !block_size.is_power_of_two() || block_size < MIN_BLOCK_SIZE {
+            return Err(BackupError::InvalidArgument);
+        }
+        Ok(Self {
+            volume_size,
+            block_size,
+            changed: Bitmap::new(volume_size.div_ceil(block_size as u64)),
+            last_backup: None,
+            next_generation: 1,
+            in_flight: None,
+        })
+    }
+
+    pub fn volume_size(&self) -> u64 {
+        self.volume_size
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Generation of the last completed backup
+    pub fn last_backup(&self) -> Option<u64> {
+        self.last_backup
+    }
+
+    /// No backup to build an incremental one on
+    pub fn needs_full_backup(&self) -> bool {
+        self.last_backup.is_none()
+    }
+
+    pub fn changed_bytes(&self) -> u64 {
+        (self.changed.count() * self.block_size as u64).min(self.volume_size)
+    }
+
+    /// Record a write (or trim) of `length` bytes at `offset`
+    pub fn record_write(&mut self, offset: u64, length: u64) {
+        if length == 0 {
+            return;
+        }
+        let block_size = self.block_size as u64;
+        let first = offset / block_size;
+        let last = (offset.saturating_add(length) - 1) / block_size;
+        self.changed.set_range(first, last - first + 1);
+    }
+
+    /// Follow a resize of the volume; space added counts as changed
+    pub fn resize(&mut self, volume_size: u64) {
+        self.volume_size = volume_size;
+        self.changed.resize(volume_size.div_ceil(self.block_size as u64));
+    }
+
+    /// Start a backup of everything changed since the last one, or of the
+    /// whole volume when `full` is set or there is no previous backup
+    pub fn begin_backup(&mut self, full: bool) -> Result<BackupPoint, BackupError> {
+        if self.in_flight.is_some() {
+            return Err(BackupError::Busy);
+        }
+        let base = if full { None } else { self.last_backup };
+        let fresh = Bitmap::new(self.changed.len());
+        let mut changed = core::mem::replace(&mut self.changed, fresh);
+        if base.is_none() {
+            changed.set_all();
+        }
+
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.in_flight = Some(generation);
+        Ok(BackupPoint { base, generation, volume_size: self.volume_size, block_size: self.block_size, changed })
+    }
+
+    /// The stream of backup `generation` is safely stored: later backups
+    /// build on it
+    pub fn complete_backup(&mut self, generation: u64) -> Result<(), BackupError> {
+        if self.in_flight != Some(generation) {
+            return Err(BackupError::InvalidArgument);
+        }
+        self.in_flight = None;
+        self.last_backup = Some(generation);
+        Ok(())
+    }
+
+    /// Give the blocks of a failed backup back to the tracker
+    pub fn abort_backup(&mut self, point: BackupPoint) {
+        if self.in_flight == Some(point.generation) {
+            self.in_flight = None;
+        }
+        self.changed.merge(&point.changed);
+    }
+
+    /// Persistent form of the tracker; `clean` only at orderly shutdown
+    pub fn encode(&self, clean: bool) -> Vec<u8> {
+        let mut out = Vec::with_capacity(RECORD_HEADER_SIZE + self.changed.words.len() * 8 + RECORD_DIGEST_SIZE);
+        out.extend_from_slice(RECORD_MAGIC);
+        out.extend_from_slice(&RECORD_VERSION.to_le_bytes());
+        out.extend_from_slice(&(if clean { RECORD_CLEAN } else { 0 }).to_le_bytes());
+        out.extend_from_slice(&self.block_size.to_le_bytes());
+        out.extend_from_slice(&self.volume_size.to_le_bytes());
+        out.extend_from_slice(&self.last_backup.unwrap_or(0).to_le_bytes());
+        out.extend_from_slice(&self.next_generation.to_le_bytes());
+        for word in self.changed.words.iter() {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        let digest = record_digest(&out);
+        out.extend_from_slice(&digest);
+        out
+    }
+
+    /// Rebuild a tracker from its record. A backup in flight when the
+    /// record was written is forgotten; its blocks were saved as changed.
+    pub fn decode(record: &[u8]) -> Result<Self, BackupError> {
+        if record.len() < RECORD_HEADER_SIZE + RECORD_DIGEST_SIZE || &record[..4] != RECORD_MAGIC {
+            return Err(BackupError::Corrupt);
+        }
+        let (body, digest) = record.split_at(record.len() - RECORD_DIGEST_SIZE);
+        if record_digest(body) != digest || read_u32(body, 4) != RECORD_VERSION {
+            return Err(BackupError::Corrupt);
+        }
+
+        let clean = read_u32(body, 8) & RECORD_CLEAN != 0;
+        let mut tracker = Self::new(read_u64(body, 16), read_u32(body, 12)).map_err(|_| BackupError::Corrupt)?;
+        let words = &body[RECORD_HEADER_SIZE..];
+        if words.len() != tracker.changed.words.len() * 8 {
+            return Err(BackupError::Corrupt);
+        }
+        for (index, word) in tracker.changed.words.iter_mut().enumerate() {
+            *word = read_u64(words, index * 8);
+        }
+        tracker.next_generation = read_u64(body, 32).max(1);
+        tracker.last_backup = match read_u64(body, 24) {
+            // Writes may be missing from the bitmap: only a full backup is safe
+            _ if !clean => None,
+            0 => None,
+            generation => Some(generation),
+        };
+        Ok(tracker)
+    }
+
+    /// Tracker of a volume from its saved record, or a new one when the
+    /// record is missing, damaged or describes another geometry
+    pub fn load(record: Option<&[u8]>, volume_size: u64, block_size: u32) -> Result<Self, BackupError> {
+        match record.map(Self::decode) {
+            Some(Ok(mut tracker)) if tracker.block_size == block_size => {
+                if tracker.volume_size != volume_size {
+                    tracker.resize(volume_size);
+                }
+                Ok(tracker)
+            }
+            _ => Self::new(volume_size, block_size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+
+    #[test]
+    fn test_backup_points_split_writes() {
+        let mut tracker = ChangeTracker::new(16 * MIB, 65536).unwrap();
+        assert_eq!(ChangeTracker::new(MIB, 1000).err(), Some(BackupError::InvalidArgument));
+
+        // The first backup is full whatever was written
+        tracker.record_write(0, 1);
+        let full = tracker.begin_backup(false).unwrap();
+        assert_eq!((full.base, full.changed_bytes()), (None, 16 * MIB));
+        assert_eq!(tracker.begin_backup(false).err(), Some(BackupError::Busy));
+        tracker.record_write(65535, 2);
+        tracker.complete_backup(full.generation).unwrap();
+
+        let incremental = tracker.begin_backup(false).unwrap();
+        assert_eq!(incremental.base, Some(full.generation));
+        assert_eq!(incremental.changed.next_run(0, 64), Some((0, 2)));
+        assert_eq!(incremental.changed.next_run(2, 64), None);
+
+        // A failed backup leaves its blocks to the next one
+        tracker.record_write(4 * MIB, 65536);
+        tracker.abort_backup(incremental);
+        let retry = tracker.begin_backup(false).unwrap();
+        assert_eq!(retry.base, Some(full.generation));
+        assert_eq!(retry.changed.count(), 3);
+    }
+
+    #[test]
+    fn test_records_survive_reboots() {
+        let mut tracker = ChangeTracker::new(4 * MIB, 4096).unwrap();
+        let point = tracker.begin_backup(false).unwrap();
+        tracker.complete_backup(point.generation).unwrap();
+        tracker.record_write(8192, 4096);
+
+        let restored = ChangeTracker::decode(&tracker.encode(true)).unwrap();
+        assert_eq!(restored.last_backup(), Some(point.generation));
+        assert_eq!(restored.changed_bytes(), 4096);
+
+        // A record left unclean by a crash forces a full backup
+        let crashed = ChangeTracker::decode(&tracker.encode(false)).unwrap();
+        assert!(crashed.needs_full_backup());
+
+        let mut damaged = tracker.encode(true);
+        damaged[45] ^= 1;
+        assert_eq!(ChangeTracker::decode(&damaged).err(), Some(BackupError::Corrupt));
+        assert!(ChangeTracker::load(Some(&damaged), 4 * MIB, 4096).unwrap().needs_full_backup());
+
+        // Growing the volume marks the new space changed
+        let mut grown = ChangeTracker::load(Some(&tracker.encode(true)), 4 * MIB + 8192, 4096).unwrap();
+        assert_eq!(grown.changed_bytes(), 3 * 4096);
+        grown.resize(4096);
+        assert_eq!(grown.changed_bytes(), 0);
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * Orion Operating System - Incremental Backup
+ *
+ * Changed-block tracking for block volumes and the backup stream format
+ * built on it. A volume driver records every write in a ChangeTracker;
+ * taking a backup point hands over the blocks changed since the previous
+ * point and starts a fresh bitmap, so each backup carries only what was
+ * modified in between. The stream is self-verifying: every extent has its
+ * own digest and the trailer covers the whole stream, and restoring checks
+ * that incremental streams are applied on top of the backup they follow.
+ *
+ * The crate is transport agnostic: streams are written to a BackupSink
+ * and read from a BackupSource, implemented by files, NBD exports or a
+ * connection to a remote node.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod cbt;
+pub mod restore;
+pub mod stream;
+
+pub use cbt::{BackupPoint, ChangeTracker};
+pub use restore::{restore_from, Restore, RestoreSummary};
+pub use stream::{BackupJob, BackupSummary, Extent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupError {
+    InvalidArgument,
+    /// A backup of the volume is already running
+    Busy,
+    /// Malformed tracking record or stream
+    Corrupt,
+    /// Extent digest does not match its data
+    DigestMismatch {
+        offset: u64,
+    },
+    /// Incremental stream applied on top of the wrong backup
+    ChainMismatch {
+        expected: Option<u64>,
+        found: Option<u64>,
+    },
+    /// Stream ended before its trailer
+    Truncated,
+    /// Failure of the sink, source or volume
+    Io,
+}
+
+/// Destination of a backup stream
+pub trait BackupSink {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BackupError>;
+}
+
+/// Origin of a stream to restore; returns 0 at the end
+pub trait BackupSource {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, BackupError>;
+}
+
+/// Volume a stream is restored onto
+pub trait RestoreTarget {
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), BackupError>;
+}
+
+impl<F: FnMut(u64, &[u8]) -> Result<(), BackupError>> RestoreTarget for F {
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), BackupError> {
+        self(offset, data)
+    }
+}
+
+impl BackupSink for alloc::vec::Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BackupError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+/*
+ * Orion Operating System - Backup Restore
+ *
+ * Incremental parser for backup streams. Bytes are fed as they arrive
+ * from the source and each extent is handed to the caller's writer only
+ * once its digest has been checked, so a damaged stream stops at the
+ * first bad extent. A volume is restored by applying its last full
+ * stream followed by every incremental one in order: each stream names
+ * the generation it builds on and is refused on top of anything else.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_crypto::sha512::{Sha512, SHA512_DIGEST_SIZE};
+
+use crate::stream::{
+    StreamHeader, END_HEADER_SIZE, EXTENT_HEADER_SIZE, MAX_EXTENT_SIZE, RECORD_END, RECORD_EXTENT, STREAM_HEADER_SIZE,
+};
+use crate::{BackupError, BackupSource, RestoreTarget};
+
+/// Chunk size used when pulling a stream from a BackupSource
+const READ_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub base: Option<u64>,
+    pub generation: u64,
+    pub volume_size: u64,
+    pub extents: u64,
+    pub bytes: u64,
+}
+
+pub struct Restore {
+    /// Generation the target holds before this stream
+    restored: Option<u64>,
+    header: Option<StreamHeader>,
+    buffer: Vec<u8>,
+    hasher: Sha512,
+    extents: u64,
+    bytes: u64,
+    done: bool,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(raw)
+}
+
+impl Restore {
+    /// Restore onto a target holding backup generation `restored`, or
+    /// None when it holds nothing known and only a full stream will do
+    pub fn new(restored: Option<u64>) -> Self {
+        Self { restored, header: None, buffer: Vec::new(), hasher: Sha512::new(), extents: 0, bytes: 0, done: false }
+    }
+
+    /// Header of the stream, once received
+    pub fn header(&self) -> Option<&StreamHeader> {
+        self.header.as_ref()
+    }
+
+    /// Parse more of the stream, writing every extent that passed
+    /// verification to `target`
+    pub fn feed(&mut self, data: &[u8], target: &mut dyn RestoreTarget) -> Result<(), BackupError> {
+        self.buffer.extend_from_slice(data);
+        while let Some(consumed) = self.parse(target)? {
+            self.buffer.drain(..consumed);
+        }
+        Ok(())
+    }
+
+    /// Handle the record at the front of the buffer; None until it is complete
+    fn parse(&mut self, target: &mut dyn RestoreTarget) -> Result<Option<usize>, BackupError> {
+        let buffer = &self.buffer;
+        if self.done {
+            return if buffer.is_empty() { Ok(None) } else { Err(BackupError::Corrupt) };
+        }
+
+        let header = match self.header {
+            Some(header) => header,
+            None => {
+                if buffer.len() < STREAM_HEADER_SIZE {
+                    return Ok(None);
+                }
+                let header = StreamHeader::decode(buffer)?;
+                if header.base.is_some() && header.base != self.restored {
+                    return Err(BackupError::ChainMismatch { expected: self.restored, found: header.base });
+                }
+                self.hasher.update(&buffer[..STREAM_HEADER_SIZE]);
+                self.header = Some(header);
+                return Ok(Some(STREAM_HEADER_SIZE));
+            }
+        };
+
+        if buffer.len() < 4 {
+            return Ok(None);
+        }
+        match read_u32(buffer, 0) {
+            RECORD_EXTENT => {
+                if buffer.len() < EXTENT_HEADER_SIZE {
+                    return Ok(None);
+                }
+                let length = read_u32(buffer, 4);
+                let offset = read_u64(buffer, 8);
+                let in_volume = offset.checked_add(length as u64).is_some_and(|end| end <= header.volume_size);
+                if length == 0 || length > MAX_EXTENT_SIZE || !in_volume {
+                    return Err(BackupError::Corrupt);
+                }
+                let data_end = EXTENT_HEADER_SIZE + length as usize;
+                let record_size = data_end + SHA512_DIGEST_SIZE;
+                if buffer.len() < record_size {
+                    return Ok(None);
+                }
+                let data = &buffer[EXTENT_HEADER_SIZE..data_end];
+                if Sha512::digest(data)[..] != buffer[data_end..record_size] {
+                    return Err(BackupError::DigestMismatch { offset });
+                }
+                target.write_at(offset, data)?;
+                self.hasher.update(&buffer[..record_size]);
+                self.extents += 1;
+                self.bytes += length as u64;
+                Ok(Some(record_size))
+            }
+            RECORD_END => {
+                let record_size = END_HEADER_SIZE + SHA512_DIGEST_SIZE;
+                if buffer.len() < record_size {
+                    return Ok(None);
+                }
+                if read_u64(buffer, 8) != self.extents || read_u64(buffer, 16) != self.bytes {
+                    return Err(BackupError::Corrupt);
+                }
+                let mut hasher = core::mem::take(&mut self.hasher);
+                hasher.update(&buffer[..END_HEADER_SIZE]);
+                if hasher.finalize()[..] != buffer[END_HEADER_SIZE..record_size] {
+                    return Err(BackupError::Corrupt);
+                }
+                self.done = true;
+                Ok(Some(record_size))
+            }
+            _ => Err(BackupError::Corrupt),
+        }
+    }
+
+    /// Check that the whole stream was received
+    pub fn finish(self) -> Result<RestoreSummary, BackupError> {
+        let header = self.header.ok_or(BackupError::Truncated)?;
+        if !self.done {
+            return Err(BackupError::Truncated);
+        }
+        Ok(RestoreSummary {
+            base: header.base,
+            generation: header.generation,
+            volume_size: header.volume_size,
+            extents: self.extents,
+            bytes: self.bytes,
+        })
+    }
+}
+
+/// Restore a whole stream read from `source`
+pub fn restore_from(
+    source: &mut dyn BackupSource,
+    restored: Option<u64>,
+    target: &mut dyn RestoreTarget,
+) -> Result<RestoreSummary, BackupError> {
+    let mut restore = Restore::new(restored);
+    let mut chunk = vec![0u8; READ_CHUNK];
+    loop {
+        let read = source.read(&mut chunk)?;
+        if read == 0 {
+            return restore.finish();
+        }
+        restore.feed(&chunk[..read], target)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbt::ChangeTracker;
+    use crate::stream::BackupJob;
+    use crate::BackupSink;
+
+    const VOLUME_SIZE: u64 = 64 * 1024;
+    const BLOCK_SIZE: u32 = 4096;
+
+    /// Stream the changed blocks of `volume` as the tracker sees them
+    fn backup(tracker: &mut ChangeTracker, volume: &[u8], full: bool) -> Vec<u8> {
+        let point = tracker.begin_backup(full).unwrap();
+        let mut job = BackupJob::new(point, 2 * BLOCK_SIZE);
+        let mut stream = job.header();
+        while let Some(extent) = job.next_extent() {
+            let range = extent.offset as usize..extent.offset as usize + extent.length as usize;
+            let record = job.encode_extent(extent, &volume[range]).unwrap();
+            stream.write(&record).unwrap();
+        }
+        let (trailer, summary) = job.finish();
+        stream.write(&trailer).unwrap();
+        tracker.complete_backup(summary.generation).unwrap();
+        stream
+    }
+
+    fn write(volume: &mut [u8], tracker: &mut ChangeTracker, offset: usize, data: &[u8]) {
+        volume[offset..offset + data.len()].copy_from_slice(data);
+        tracker.record_write(offset as u64, data.len() as u64);
+    }
+
+    fn apply(target: &mut [u8], restored: Option<u64>, stream: &[u8]) -> Result<RestoreSummary, BackupError> {
+        let mut restore = Restore::new(restored);
+        // Feed in odd-sized pieces to exercise partial records
+        for piece in stream.chunks(1000) {
+            restore.feed(piece, &mut |offset: u64, data: &[u8]| {
+                target[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+                Ok(())
+            })?;
+        }
+        restore.finish()
+    }
+
+    #[test]
+    fn test_full_and_incremental_chain() {
+        let mut volume = vec![0u8; VOLUME_SIZE as usize];
+        let mut tracker = ChangeTracker::new(VOLUME_SIZE, BLOCK_SIZE).unwrap();
+        write(&mut volume, &mut tracker, 100, b"first");
+        let full = backup(&mut tracker, &volume, false);
+
+        write(&mut volume, &mut tracker, 8190, b"straddles two blocks");
+        write(&mut volume, &mut tracker, 60000, b"near the end");
+        let incremental = backup(&mut tracker, &volume, false);
+        // Only the three touched blocks travel
+        assert!(incremental.len() < 4 * BLOCK_SIZE as usize);
+
+        let mut target = vec![0xAAu8; VOLUME_SIZE as usize];
+        let summary = apply(&mut target, None, &full).unwrap();
+        assert_eq!((summary.base, summary.bytes), (None, VOLUME_SIZE));
+
+        // Incrementals only apply on top of the backup they follow
+        assert_eq!(
+            apply(&mut target, None, &incremental).err(),
+            Some(BackupError::ChainMismatch { expected: None, found: Some(summary.generation) })
+        );
+        let summary = apply(&mut target, Some(summary.generation), &incremental).unwrap();
+        assert_eq!(summary.extents, 2);
+        assert_eq!(target, volume);
+    }
+
+    #[test]
+    fn test_damaged_streams_are_refused() {
+        let volume = vec![7u8; VOLUME_SIZE as usize];
+        let mut tracker = ChangeTracker::new(VOLUME_SIZE, BLOCK_SIZE).unwrap();
+        let stream = backup(&mut tracker, &volume, true);
+        let mut target = vec![0u8; VOLUME_SIZE as usize];
+
+        let mut flipped = stream.clone();
+        flipped[STREAM_HEADER_SIZE + EXTENT_HEADER_SIZE + 5] ^= 1;
+        assert_eq!(apply(&mut target, None, &flipped).err(), Some(BackupError::DigestMismatch { offset: 0 }));
+
+        assert_eq!(apply(&mut target, None, &stream[..stream.len() - 1]).err(), Some(BackupError::Truncated));
+
+        // Dropping a whole extent record breaks the stream digest
+        let record = EXTENT_HEADER_SIZE + 2 * BLOCK_SIZE as usize + SHA512_DIGEST_SIZE;
+        let mut spliced = stream[..STREAM_HEADER_SIZE].to_vec();
+        spliced.extend_from_slice(&stream[STREAM_HEADER_SIZE + record..]);
+        assert_eq!(apply(&mut target, None, &spliced).err(), Some(BackupError::Corrupt));
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Orion Operating System - Backup Stream
+ *
+ * Layout of a backup stream, all fields little-endian:
+ *
+ *   header   "OBAK" version:u32 flags:u32 block_size:u32 volume_size:u64
+ *            base:u64 generation:u64, padded to 64 bytes
+ *   extent   type=1:u32 length:u32 offset:u64 data[length] sha512(data)
+ *   end      type=2:u32 reserved:u32 extents:u64 bytes:u64 sha512(stream)
+ *
+ * `base` is the generation of the backup an incremental stream applies
+ * on (0 and the FULL flag for a full backup). The final digest covers
+ * every byte of the stream before it, so a stream cut or spliced
+ * together from others is refused even when each extent checks out.
+ *
+ * A BackupJob walks the changed blocks of a backup point and leaves the
+ * reads to the caller, so drivers with asynchronous I/O can use it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::sha512::{Sha512, SHA512_DIGEST_SIZE};
+
+use crate::cbt::BackupPoint;
+use crate::BackupError;
+
+pub const STREAM_MAGIC: &[u8; 4] = b"OBAK";
+pub const STREAM_VERSION: u32 = 1;
+pub const STREAM_HEADER_SIZE: usize = 64;
+
+/// Header flag: the stream holds the whole volume
+pub const STREAM_FULL: u32 = 1 << 0;
+
+pub const RECORD_EXTENT: u32 = 1;
+pub const RECORD_END: u32 = 2;
+pub const EXTENT_HEADER_SIZE: usize = 16;
+pub const END_HEADER_SIZE: usize = 24;
+
+/// Largest extent a stream may carry
+pub const MAX_EXTENT_SIZE: u32 = 16 << 20;
+pub const DEFAULT_EXTENT_SIZE: u32 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamHeader {
+    pub base: Option<u64>,
+    pub generation: u64,
+    pub volume_size: u64,
+    pub block_size: u32,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(raw)
+}
+
+impl StreamHeader {
+    pub fn encode(&self) -> [u8; STREAM_HEADER_SIZE] {
+        let mut out = [0u8; STREAM_HEADER_SIZE];
+        let flags = if self.base.is_none() { STREAM_FULL } else { 0 };
+        out[..4].copy_from_slice(STREAM_MAGIC);
+        out[4..8].copy_from_slice(&STREAM_VERSION.to_le_bytes());
+        out[8..12].copy_from_slice(&flags.to_le_bytes());
+        out[12..16].copy_from_slice(&self.block_size.to_le_bytes());
+        out[16..24].copy_from_slice(&self.volume_size.to_le_bytes());
+        out[24..32].copy_from_slice(&self.base.unwrap_or(0).to_le_bytes());
+        out[32..40].copy_from_slice(&self.generation.to_le_bytes());
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, BackupError> {
+        if data.len() < STREAM_HEADER_SIZE || &data[..4] != STREAM_MAGIC || read_u32(data, 4) != STREAM_VERSION {
+            return Err(BackupError::Corrupt);
+        }
+        let full = read_u32(data, 8) & STREAM_FULL != 0;
+        let base = read_u64(data, 24);
+        if full != (base == 0) {
+            return Err(BackupError::Corrupt);
+        }
+        Ok(Self {
+            base: (!full).then_some(base),
+            generation: read_u64(data, 32),
+            volume_size: read_u64(data, 16),
+            block_size: read_u32(data, 12),
+        })
+    }
+}
+
+/// Byte range of the volume to read for the next record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    pub offset: u64,
+    pub length: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupSummary {
+    pub base: Option<u64>,
+    pub generation: u64,
+    pub extents: u64,
+    pub bytes: u64,
+}
+
+pub struct BackupJob {
+    point: BackupPoint,
+    next_block: u64,
+    max_blocks: u64,
+    hasher: Sha512,
+    extents: u64,
+    bytes: u64,
+}
+
+impl BackupJob {
+    /// Stream the blocks of `point` in extents of about `max_extent` bytes,
+    /// rounded down to whole blocks but at least one
+    pub fn new(point: BackupPoint, max_extent: u32) -> Self {
+        let max_blocks = (max_extent.min(MAX_EXTENT_SIZE) / point.block_size).max(1) as u64;
+        Self { point, next_block: 0, max_blocks, hasher: Sha512::new(), extents: 0, bytes: 0 }
+    }
+
+    pub fn point(&self) -> &BackupPoint {
+        &self.point
+    }
+
+    /// Give the point back, to return its blocks to the tracker on failure
+    pub fn into_point(self) -> BackupPoint {
+        self.point
+    }
+
+    /// First bytes of the stream
+    pub fn header(&mut self) -> Vec<u8> {
+        let header = StreamHeader {
+            base: self.point.base,
+            generation: self.point.generation,
+            volume_size: self.point.volume_size,
+            block_size: self.point.block_size,
+        }
+        .encode();
+        self.hasher.update(&header);
+        header.to_vec()
+    }
+
+    /// Next range of changed blocks, None once all were returned
+    pub fn next_extent(&mut self) -> Option<Extent> {
+        let (first, count) = self.point.changed.next_run(self.next_block, self.max_blocks)?;
+        self.next_block = first + count;
+        let block_size = self.point.block_size as u64;
+        let offset = first * block_size;
+        let end = ((first + count) * block_size).min(self.point.volume_size);
+        Some(Extent { offset, length: (end - offset) as u32 })
+    }
+
+    /// Record carrying the data read for `extent`
+    pub fn encode_extent(&mut self, extent: Extent, data: &[u8]) -> Result<Vec<u8>, BackupError> {
+        if data.len() != extent.length as usize {
+            return Err(BackupError::InvalidArgument);
+        }
+        let mut out = Vec::with_capacity(EXTENT_HEADER_SIZE + data.len() + SHA512_DIGEST_SIZE);
+        out.extend_from_slice(&RECORD_EXTENT.to_le_bytes());
+        out.extend_from_slice(&extent.length.to_le_bytes());
+        out.extend_from_slice(&extent.offset.to_le_bytes());
+        out.extend_from_slice(data);
+        out.extend_from_slice(&Sha512::digest(data));
+        self.hasher.update(&out);
+        self.extents += 1;
+        self.bytes += data.len() as u64;
+        Ok(out)
+    }
+
+    /// Closing record of the stream
+    pub fn finish(&mut self) -> (Vec<u8>, BackupSummary) {
+        let mut out = Vec::with_capacity(END_HEADER_SIZE + SHA512_DIGEST_SIZE);
+        out.extend_from_slice(&RECORD_END.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&self.extents.to_le_bytes());
+        out.extend_from_slice(&self.bytes.to_le_bytes());
+        let mut hasher = core::mem::take(&mut self.hasher);
+        hasher.update(&out);
+        out.extend_from_slice(&hasher.finalize());
+        let summary = BackupSummary {
+            base: self.point.base,
+            generation: self.point.generation,
+            extents: self.extents,
+            bytes: self.bytes,
+        };
+        (out, summary)
+    }
+}
@@ -0,0 +1,487 @@
+/*
+ * Orion Operating System - Flush/FUA Ordering
+ *
+ * A BarrierQueue sits between the filesystem's requests and the device
+ * queue. Requests carry the usual journaling flags: PREFLUSH asks that
+ * every write completed before it be made durable before it starts, FUA
+ * that its own data be durable when it completes. The queue turns them
+ * into device commands:
+ *
+ *   - a flush is issued only once the writes in flight have completed,
+ *     or once the queue is empty on devices where FLUSH is not a queued
+ *     command, and nothing submitted after it starts before it completes
+ *   - FUA goes out as a flag on the write when the device supports it and
+ *     becomes a flush after the write when it does not
+ *   - without a volatile write cache flushes complete at once and FUA is
+ *     dropped, as every completed write is already durable
+ *
+ * Requests are otherwise dispatched in submission order with no limit on
+ * how many are in flight; the driver applies its own queue depth by not
+ * asking for more commands. Together this is the contract of
+ * durability.rs, which the torture test below checks against a device
+ * whose cache loses what it likes on a crash.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+use crate::control::{STATUS_EIO, STATUS_ENODEV, STATUS_ETIMEDOUT, STATUS_OK};
+
+/// Make writes completed before the request durable before it starts
+pub const REQ_PREFLUSH: u8 = 1 << 0;
+/// Make the request's own data durable before it completes
+pub const REQ_FUA: u8 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoOp {
+    Read,
+    Write,
+    Discard,
+    Flush,
+}
+
+impl IoOp {
+    fn modifies(self) -> bool {
+        matches!(self, IoOp::Write | IoOp::Discard)
+    }
+}
+
+/// Outcome of a command or request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoStatus {
+    Ok,
+    /// The device could not read or write the medium
+    MediaError,
+    /// No completion in time; the command was aborted or lost to a reset
+    Timeout,
+    /// The device refused the command or failed for another reason
+    DeviceError,
+    /// The device was given up after failed resets
+    Offline,
+}
+
+impl IoStatus {
+    pub fn is_ok(self) -> bool {
+        self == IoStatus::Ok
+    }
+
+    /// Status of a control reply
+    pub fn errno(self) -> i32 {
+        match self {
+            IoStatus::Ok => STATUS_OK,
+            IoStatus::MediaError | IoStatus::DeviceError => STATUS_EIO,
+            IoStatus::Timeout => STATUS_ETIMEDOUT,
+            IoStatus::Offline => STATUS_ENODEV,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request<T> {
+    pub op: IoOp,
+    pub flags: u8,
+    /// Caller's handle for the request, returned on completion
+    pub tag: T,
+}
+
+/// Durability features of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceCache {
+    /// Completed writes may sit in a cache lost on power failure
+    pub volatile: bool,
+    /// Writes can carry a FUA flag
+    pub fua: bool,
+    /// FLUSH may be queued next to other commands (NVMe, but not the
+    /// non-queued ATA FLUSH CACHE EXT)
+    pub queued_flush: bool,
+}
+
+/// Command for the driver to issue, and report with `complete(id, ..)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command<T> {
+    Io { id: u64, op: IoOp, fua: bool, tag: T },
+    Flush { id: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    PreFlush,
+    Data,
+    PostFlush,
+}
+
+struct Entry<T> {
+    request: Request<T>,
+    stage: Stage,
+}
+
+pub struct BarrierQueue<T> {
+    cache: DeviceCache,
+    waiting: VecDeque<Entry<T>>,
+    in_flight: BTreeMap<u64, Entry<T>>,
+    next_id: u64,
+    /// A flush is in flight; nothing waiting may start before it completes
+    barrier: bool,
+    completed: Vec<(T, IoStatus)>,
+}
+
+impl<T: Copy> BarrierQueue<T> {
+    pub fn new(cache: DeviceCache) -> Self {
+        Self {
+            cache,
+            waiting: VecDeque::new(),
+            in_flight: BTreeMap::new(),
+            next_id: 1,
+            barrier: false,
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn cache(&self) -> &DeviceCache {
+        &self.cache
+    }
+
+    pub fn submit(&mut self, request: Request<T>) {
+        let preflush = self.cache.volatile && (request.op == IoOp::Flush || request.flags & REQ_PREFLUSH != 0);
+        if request.op == IoOp::Flush && !preflush {
+            self.completed.push((request.tag, IoStatus::Ok));
+            return;
+        }
+        let stage = if preflush { Stage::PreFlush } else { Stage::Data };
+        self.waiting.push_back(Entry { request, stage });
+    }
+
+    /// Next command that may be issued now, None until a completion
+    /// lets more through
+    pub fn next_command(&mut self) -> Option<Command<T>> {
+        if self.barrier {
+            return None;
+        }
+        let entry = self.waiting.front()?;
+        let id = self.next_id;
+        let command = match entry.stage {
+            Stage::PreFlush | Stage::PostFlush => {
+                let busy = if self.cache.queued_flush {
+                    self.in_flight.values().any(|entry| entry.request.op.modifies())
+                } else {
+                    !self.in_flight.is_empty()
+                };
+                if busy {
+                    return None;
+                }
+                self.barrier = true;
+                Command::Flush { id }
+            }
+            Stage::Data => {
+                let request = entry.request;
+                let fua = request.flags & REQ_FUA != 0 && self.cache.volatile && self.cache.fua;
+                Command::Io { id, op: request.op, fua, tag: request.tag }
+            }
+        };
+        let entry = self.waiting.pop_front()?;
+        self.in_flight.insert(id, entry);
+        self.next_id += 1;
+        Some(command)
+    }
+
+    /// Report the outcome of command `id`
+    pub fn complete(&mut self, id: u64, status: IoStatus) {
+        let Some(mut entry) = self.in_flight.remove(&id) else {
+            return;
+        };
+        let request = entry.request;
+        match entry.stage {
+            Stage::PreFlush => {
+                self.barrier = false;
+                if status.is_ok() && request.op != IoOp::Flush {
+                    // Still at the head: nothing started while the flush ran
+                    entry.stage = Stage::Data;
+                    self.waiting.push_front(entry);
+                } else {
+                    self.completed.push((request.tag, status));
+                }
+            }
+            Stage::Data => {
+                let emulate_fua = request.flags & REQ_FUA != 0 && self.cache.volatile && !self.cache.fua;
+                if status.is_ok() && emulate_fua {
+                    entry.stage = Stage::PostFlush;
+                    self.waiting.push_front(entry);
+                } else {
+                    self.completed.push((request.tag, status));
+                }
+            }
+            Stage::PostFlush => {
+                self.barrier = false;
+                self.completed.push((request.tag, status));
+            }
+        }
+    }
+
+    /// Requests finished since the last call, with their outcome
+    pub fn take_completed(&mut self) -> Vec<(T, IoStatus)> {
+        core::mem::take(&mut self.completed)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.waiting.is_empty() && self.in_flight.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::durability::DurabilityOracle;
+    use alloc::vec;
+
+    const WRITE_BACK: DeviceCache = DeviceCache { volatile: true, fua: true, queued_flush: true };
+
+    fn request(op: IoOp, flags: u8, tag: u32) -> Request<u32> {
+        Request { op, flags, tag }
+    }
+
+    fn io_id(command: Option<Command<u32>>) -> u64 {
+        match command {
+            Some(Command::Io { id, .. }) => id,
+            other => panic!("expected I/O, got {:?}", other),
+        }
+    }
+
+    fn flush_id(command: Option<Command<u32>>) -> u64 {
+        match command {
+            Some(Command::Flush { id }) => id,
+            other => panic!("expected flush, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_flush_drains_writes_and_holds_later_requests() {
+        let mut queue = BarrierQueue::new(WRITE_BACK);
+        queue.submit(request(IoOp::Write, 0, 1));
+        queue.submit(request(IoOp::Read, 0, 2));
+        queue.submit(request(IoOp::Flush, 0, 3));
+        queue.submit(request(IoOp::Write, 0, 4));
+
+        let write = io_id(queue.next_command());
+        let read = io_id(queue.next_command());
+        assert_eq!(queue.next_command(), None);
+        // Outstanding reads do not hold a queued flush back
+        queue.complete(write, IoStatus::Ok);
+        let flush = flush_id(queue.next_command());
+        assert_eq!(queue.next_command(), None);
+        queue.complete(read, IoStatus::Ok);
+        queue.complete(flush, IoStatus::Ok);
+        assert_eq!(queue.take_completed(), vec![(1, IoStatus::Ok), (2, IoStatus::Ok), (3, IoStatus::Ok)]);
+        io_id(queue.next_command());
+    }
+
+    #[test]
+    fn test_fua_is_emulated_with_a_post_flush() {
+        let cache = DeviceCache { volatile: true, fua: false, queued_flush: false };
+        let mut queue = BarrierQueue::new(cache);
+        // A journal commit record followed by an unrelated read
+        queue.submit(request(IoOp::Write, REQ_PREFLUSH | REQ_FUA, 1));
+        queue.submit(request(IoOp::Read, 0, 2));
+
+        let preflush = flush_id(queue.next_command());
+        assert_eq!(queue.next_command(), None);
+        queue.complete(preflush, IoStatus::Ok);
+        let commit = match queue.next_command() {
+            Some(Command::Io { id, op: IoOp::Write, fua: false, tag: 1 }) => id,
+            other => panic!("expected the commit write, got {:?}", other),
+        };
+        let read = io_id(queue.next_command());
+        queue.complete(commit, IoStatus::Ok);
+        // A non-queued flush waits for the read as well
+        assert_eq!(queue.next_command(), None);
+        queue.complete(read, IoStatus::Ok);
+        assert_eq!(queue.take_completed(), vec![(2, IoStatus::Ok)]);
+        let postflush = flush_id(queue.next_command());
+        queue.complete(postflush, IoStatus::Ok);
+        assert_eq!(queue.take_completed(), vec![(1, IoStatus::Ok)]);
+        assert!(queue.is_idle());
+    }
+
+    #[test]
+    fn test_native_fua_and_write_through_devices() {
+        let mut queue = BarrierQueue::new(WRITE_BACK);
+        queue.submit(request(IoOp::Write, REQ_FUA, 1));
+        assert!(matches!(queue.next_command(), Some(Command::Io { fua: true, .. })));
+
+        let write_through = DeviceCache { volatile: false, fua: false, queued_flush: false };
+        let mut queue = BarrierQueue::new(write_through);
+        queue.submit(request(IoOp::Flush, 0, 1));
+        queue.submit(request(IoOp::Write, REQ_PREFLUSH | REQ_FUA, 2));
+        assert_eq!(queue.take_completed(), vec![(1, IoStatus::Ok)]);
+        let write = io_id(queue.next_command());
+        queue.complete(write, IoStatus::Ok);
+        assert_eq!(queue.take_completed(), vec![(2, IoStatus::Ok)]);
+    }
+
+    #[test]
+    fn test_failed_preflush_fails_the_request() {
+        let mut queue = BarrierQueue::new(WRITE_BACK);
+        queue.submit(request(IoOp::Write, REQ_PREFLUSH, 1));
+        queue.submit(request(IoOp::Write, 0, 2));
+        let preflush = flush_id(queue.next_command());
+        queue.complete(preflush, IoStatus::MediaError);
+        assert_eq!(queue.take_completed(), vec![(1, IoStatus::MediaError)]);
+        assert!(matches!(queue.next_command(), Some(Command::Io { tag: 2, .. })));
+    }
+
+    /// xorshift64, so a failing seed replays the same run
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+    }
+
+    struct Submitted {
+        op: IoOp,
+        flags: u8,
+        block: u64,
+        generation: u64,
+        /// Oracle token of the flush it implies
+        token: Option<u64>,
+        issued: bool,
+        done: bool,
+    }
+
+    impl Submitted {
+        /// Nothing submitted after it may start anymore because of it
+        fn passed(&self) -> bool {
+            if self.op == IoOp::Flush {
+                self.done
+            } else {
+                self.issued || self.done
+            }
+        }
+    }
+
+    /// Device whose volatile cache writes blocks back whenever it likes,
+    /// and loses them on a crash
+    #[derive(Default)]
+    struct Device {
+        cache: BTreeMap<u64, u64>,
+        medium: BTreeMap<u64, u64>,
+    }
+
+    const TORTURE_BLOCKS: u64 = 8;
+    const TORTURE_STEPS: usize = 1000;
+
+    fn torture(cache: DeviceCache, seed: u64) {
+        let mut rng = Rng(seed);
+        let mut queue = BarrierQueue::new(cache);
+        let mut oracle = DurabilityOracle::new();
+        let mut device = Device::default();
+        let mut requests: Vec<Submitted> = Vec::new();
+        let mut in_flight: Vec<Command<u32>> = Vec::new();
+        let mut generation = 0;
+        // Requests before it have all been passed
+        let mut passed = 0;
+
+        for _ in 0..TORTURE_STEPS {
+            let tag = requests.len() as u32;
+            let block = rng.below(TORTURE_BLOCKS);
+            let (op, flags) = match rng.below(8) {
+                0..=3 => (IoOp::Write, [0, 0, REQ_FUA, REQ_PREFLUSH, REQ_PREFLUSH | REQ_FUA][rng.below(5) as usize]),
+                4 => (IoOp::Read, 0),
+                5 => (IoOp::Flush, 0),
+                _ => (IoOp::Discard, 0),
+            };
+            // Writes overlapping in flight may land in any order: one per block
+            let overlaps = requests.iter().any(|request| request.block == block && !request.done);
+            if op != IoOp::Discard && !(op == IoOp::Write && overlaps) {
+                generation += 1;
+                let flushes = op == IoOp::Flush || flags & REQ_PREFLUSH != 0;
+                let token = flushes.then(|| oracle.flush_submitted());
+                requests.push(Submitted { op, flags, block, generation, token, issued: false, done: false });
+                queue.submit(Request { op, flags, tag });
+            }
+
+            while let Some(command) = queue.next_command() {
+                if let Command::Io { tag, .. } = command {
+                    while requests[passed..].first().is_some_and(Submitted::passed) {
+                        passed += 1;
+                    }
+                    assert!(passed >= tag as usize, "seed {}: request {} started across a barrier", seed, tag);
+                    requests[tag as usize].issued = true;
+                }
+                in_flight.push(command);
+            }
+
+            if !in_flight.is_empty() && rng.below(2) == 0 {
+                let command = in_flight.swap_remove(rng.below(in_flight.len() as u64) as usize);
+                let id = match command {
+                    Command::Io { id, op: IoOp::Write, fua, tag } => {
+                        let request = &requests[tag as usize];
+                        device.cache.insert(request.block, request.generation);
+                        if fua || !cache.volatile {
+                            device.medium.insert(request.block, request.generation);
+                        }
+                        id
+                    }
+                    Command::Io { id, .. } => id,
+                    Command::Flush { id } => {
+                        device.medium.extend(device.cache.iter());
+                        id
+                    }
+                };
+                queue.complete(id, IoStatus::Ok);
+            }
+
+            if rng.below(4) == 0 {
+                let block = rng.below(TORTURE_BLOCKS);
+                if let Some(generation) = device.cache.get(&block) {
+                    device.medium.insert(block, *generation);
+                }
+            }
+
+            for (tag, status) in queue.take_completed() {
+                assert_eq!(status, IoStatus::Ok);
+                let request = &mut requests[tag as usize];
+                request.done = true;
+                if let Some(token) = request.token {
+                    oracle.flush_completed(token);
+                }
+                if request.op == IoOp::Write {
+                    oracle.write_completed(request.block, request.generation, request.flags & REQ_FUA != 0);
+                }
+            }
+
+            // A crash now keeps the medium only
+            let violations = oracle.check(|block| device.medium.get(&block).copied().unwrap_or(0));
+            assert_eq!(violations, vec![], "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_torture_keeps_the_durability_contract() {
+        let caches = [
+            DeviceCache { volatile: true, fua: true, queued_flush: true },
+            DeviceCache { volatile: true, fua: true, queued_flush: false },
+            DeviceCache { volatile: true, fua: false, queued_flush: true },
+            DeviceCache { volatile: true, fua: false, queued_flush: false },
+            DeviceCache { volatile: false, fua: false, queued_flush: false },
+        ];
+        for cache in caches {
+            for seed in 1..=16u64 {
+                torture(cache, seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+/*
+ * Orion Operating System - Raw Disk Control
+ *
+ * Whole-disk access for tools that work below any file system or volume,
+ * such as the installer writing a partition table. Block drivers accept
+ * these requests on their IPC channel as an ioctl of length
+ * BLK_IOCTL_CONTROL. All fields are little-endian; every request starts
+ * with a 32-bit opcode and every reply with a 32-bit signed status (0 or
+ * a negative errno).
+ *
+ *   INFO                         -> block_size:u32 flags:u32 blocks:u64
+ *   READ    lba:u64 count:u32    -> data
+ *   WRITE   lba:u64 data         -> (empty)
+ *   FLUSH                        -> (empty)
+ *   READ_INTEGRITY  lba:u64 count:u32 algorithm:u32 -> tags data
+ *   WRITE_INTEGRITY lba:u64 count:u32 algorithm:u32 tags data -> (empty)
+ *
+ * A transfer covers whole logical blocks and at most MAX_TRANSFER bytes.
+ * The opcodes do not overlap those of the firmware control requests, so
+ * a driver can serve both on one channel. There is no access check here:
+ * the channel of a disk driver is only handed to the storage stack and
+ * to the installer.
+ *
+ * A failed transfer answers ETIMEDOUT when the disk stopped responding,
+ * ENODEV once the driver has given it up and EIO for media and other
+ * device errors.
+ *
+ * The INTEGRITY variants carry a checksum per block (see integrity), each
+ * tag_size bytes of the algorithm. The driver verifies a write against
+ * them before it reaches the device and checksums a read as soon as it
+ * comes off it; data that no longer matches its checksums answers
+ * EBADMSG.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::integrity::{Algorithm, Checksums};
+
+/// Ioctl length selecting raw disk control requests
+pub const BLK_IOCTL_CONTROL: u64 = 0x3020;
+
+// Opcodes
+pub const BLK_CTRL_INFO: u32 = 0x2001;
+pub const BLK_CTRL_READ: u32 = 0x2002;
+pub const BLK_CTRL_WRITE: u32 = 0x2003;
+pub const BLK_CTRL_FLUSH: u32 = 0x2004;
+pub const BLK_CTRL_READ_INTEGRITY: u32 = 0x2005;
+pub const BLK_CTRL_WRITE_INTEGRITY: u32 = 0x2006;
+
+/// INFO flags
+pub const BLK_INFO_READ_ONLY: u32 = 1 << 0;
+
+/// Largest READ or WRITE payload
+pub const MAX_TRANSFER: usize = 64 * 1024;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_ENODEV: i32 = -19;
+pub const STATUS_EROFS: i32 = -30;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_EBADMSG: i32 = -74;
+pub const STATUS_ETIMEDOUT: i32 = -110;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskInfo {
+    pub block_size: u32,
+    pub flags: u32,
+    pub blocks: u64,
+}
+
+impl DiskInfo {
+    pub fn size(&self) -> u64 {
+        self.blocks * self.block_size as u64
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ControlRequest<'a> {
+    Info,
+    Read { lba: u64, count: u32 },
+    Write { lba: u64, data: &'a [u8] },
+    Flush,
+    ReadIntegrity { lba: u64, count: u32, algorithm: Algorithm },
+    WriteIntegrity { lba: u64, checksums: Checksums, data: &'a [u8] },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+impl<'a> ControlRequest<'a> {
+    /// Decode a request for a disk with `block_size` byte blocks; None for
+    /// anything malformed or not made of whole blocks
+    pub fn decode(data: &'a [u8], block_size: u32) -> Option<Self> {
+        let block_size = block_size.max(1) as usize;
+        match read_u32(data, 0)? {
+            BLK_CTRL_INFO => Some(ControlRequest::Info),
+            BLK_CTRL_READ => {
+                let count = read_u32(data, 12)?;
+                let bytes = (count as usize).checked_mul(block_size)?;
+                (count > 0 && bytes <= MAX_TRANSFER).then_some(ControlRequest::Read { lba: read_u64(data, 4)?, count })
+            }
+            BLK_CTRL_WRITE => {
+                let payload = data.get(12..)?;
+                let whole = !payload.is_empty() && payload.len().is_multiple_of(block_size);
+                (whole && payload.len() <= MAX_TRANSFER)
+                    .then_some(ControlRequest::Write { lba: read_u64(data, 4)?, data: payload })
+            }
+            BLK_CTRL_FLUSH => Some(ControlRequest::Flush),
+            BLK_CTRL_READ_INTEGRITY => {
+                let count = read_u32(data, 12)?;
+                let algorithm = Algorithm::from_u32(read_u32(data, 16)?)?;
+                let bytes = (count as usize).checked_mul(block_size)?;
+                (count > 0 && bytes <= MAX_TRANSFER).then_some(ControlRequest::ReadIntegrity {
+                    lba: read_u64(data, 4)?,
+                    count,
+                    algorithm,
+                })
+            }
+            BLK_CTRL_WRITE_INTEGRITY => {
+                let count = read_u32(data, 12)? as usize;
+                let algorithm = Algorithm::from_u32(read_u32(data, 16)?)?;
+                let checksums = Checksums::decode(algorithm, block_size, count, data.get(20..)?)?;
+                let payload = data.get(20 + count * algorithm.tag_size()..)?;
+                (count > 0 && payload.len() == count.checked_mul(block_size)? && payload.len() <= MAX_TRANSFER)
+                    .then_some(ControlRequest::WriteIntegrity { lba: read_u64(data, 4)?, checksums, data: payload })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Payload of a READ_INTEGRITY reply: the checksums of `data`, then `data`
+pub fn encode_integrity_read(algorithm: Algorithm, block_size: u32, data: &[u8]) -> Vec<u8> {
+    let mut out = Checksums::compute(algorithm, block_size as usize, data).encode();
+    out.extend_from_slice(data);
+    out
+}
+
+pub fn encode_info(info: &DiskInfo) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16);
+    out.extend_from_slice(&info.block_size.to_le_bytes());
+    out.extend_from_slice(&info.flags.to_le_bytes());
+    out.extend_from_slice(&info.blocks.to_le_bytes());
+    out
+}
+
+/// Carries a control request to a driver and returns its reply
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Client side of the requests. Errors are negative statuses
+pub struct DiskClient<T: Transport> {
+    transport: T,
+    info: Option<DiskInfo>,
+}
+
+impl<T: Transport> DiskClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport, info: None }
+    }
+
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.transport.call(request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    /// Geometry of the disk, asked once
+    pub fn info(&mut self) -> Result<DiskInfo, i32> {
+        if let Some(info) = self.info {
+            return Ok(info);
+        }
+        let payload = self.call(&BLK_CTRL_INFO.to_le_bytes())?;
+        let info = DiskInfo {
+            block_size: read_u32(&payload, 0).ok_or(STATUS_EIO)?,
+            flags: read_u32(&payload, 4).ok_or(STATUS_EIO)?,
+            blocks: read_u64(&payload, 8).ok_or(STATUS_EIO)?,
+        };
+        if info.block_size == 0 || !MAX_TRANSFER.is_multiple_of(info.block_size as usize) {
+            return Err(STATUS_EIO);
+        }
+        self.info = Some(info);
+        Ok(info)
+    }
+
+    /// Fill `buffer`, a whole number of blocks, from `lba` on
+    pub fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        let block_size = self.info()?.block_size as usize;
+        if !buffer.len().is_multiple_of(block_size) {
+            return Err(STATUS_EINVAL);
+        }
+        for (index, chunk) in buffer.chunks_mut(MAX_TRANSFER).enumerate() {
+            let mut request = BLK_CTRL_READ.to_le_bytes().to_vec();
+            request.extend_from_slice(&(lba + (index * MAX_TRANSFER / block_size) as u64).to_le_bytes());
+            request.extend_from_slice(&((chunk.len() / block_size) as u32).to_le_bytes());
+            let data = self.call(&request)?;
+            if data.len() != chunk.len() {
+                return Err(STATUS_EIO);
+            }
+            chunk.copy_from_slice(&data);
+        }
+        Ok(())
+    }
+
+    /// Write `data`, a whole number of blocks, from `lba` on
+    pub fn write(&mut self, lba: u64, data: &[u8]) -> Result<(), i32> {
+        let block_size = self.info()?.block_size as usize;
+        if !data.len().is_multiple_of(block_size) {
+            return Err(STATUS_EINVAL);
+        }
+        for (index, chunk) in data.chunks(MAX_TRANSFER).enumerate() {
+            let mut request = Vec::with_capacity(12 + chunk.len());
+            request.extend_from_slice(&BLK_CTRL_WRITE.to_le_bytes());
+            request.extend_from_slice(&(lba + (index * MAX_TRANSFER / block_size) as u64).to_le_bytes());
+            request.extend_from_slice(chunk);
+            self.call(&request)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), i32> {
+        self.call(&BLK_CTRL_FLUSH.to_le_bytes()).map(|_| ())
+    }
+
+    /// Like `read`, with the data checksummed by the driver and verified
+    /// here; EBADMSG when it was damaged on the way up
+    pub fn read_verified(&mut self, lba: u64, buffer: &mut [u8], algorithm: Algorithm) -> Result<(), i32> {
+        let block_size = self.info()?.block_size as usize;
+        if !buffer.len().is_multiple_of(block_size) {
+            return Err(STATUS_EINVAL);
+        }
+        for (index, chunk) in buffer.chunks_mut(MAX_TRANSFER).enumerate() {
+            let count = chunk.len() / block_size;
+            let mut request = BLK_CTRL_READ_INTEGRITY.to_le_bytes().to_vec();
+            request.extend_from_slice(&(lba + (index * MAX_TRANSFER / block_size) as u64).to_le_bytes());
+            request.extend_from_slice(&(count as u32).to_le_bytes());
+            request.extend_from_slice(&algorithm.as_u32().to_le_bytes());
+            let payload = self.call(&request)?;
+            let checksums = Checksums::decode(algorithm, block_size, count, &payload).ok_or(STATUS_EIO)?;
+            let data = &payload[count * algorithm.tag_size()..];
+            if data.len() != chunk.len() {
+                return Err(STATUS_EIO);
+            }
+            checksums.verify(data).map_err(|_| STATUS_EBADMSG)?;
+            chunk.copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    /// Like `write`, with checksums computed here and verified by the
+    /// driver before the data goes to the device
+    pub fn write_verified(&mut self, lba: u64, data: &[u8], algorithm: Algorithm) -> Result<(), i32> {
+        let block_size = self.info()?.block_size as usize;
+        if !data.len().is_multiple_of(block_size) {
+            return Err(STATUS_EINVAL);
+        }
+        for (index, chunk) in data.chunks(MAX_TRANSFER).enumerate() {
+            let count = chunk.len() / block_size;
+            let checksums = Checksums::compute(algorithm, block_size, chunk);
+            let mut request = Vec::with_capacity(20 + count * algorithm.tag_size() + chunk.len());
+            request.extend_from_slice(&BLK_CTRL_WRITE_INTEGRITY.to_le_bytes());
+            request.extend_from_slice(&(lba + (index * MAX_TRANSFER / block_size) as u64).to_le_bytes());
+            request.extend_from_slice(&(count as u32).to_le_bytes());
+            request.extend_from_slice(&algorithm.as_u32().to_le_bytes());
+            request.extend_from_slice(&checksums.encode());
+            request.extend_from_slice(chunk);
+            self.call(&request)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use orion_proto::reply;
+
+    /// Disk of 512 byte blocks kept in memory, served like a driver would
+    struct MemoryDisk(Vec<u8>);
+
+    impl Transport for &mut MemoryDisk {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            let disk = &mut self.0;
+            Some(match ControlRequest::decode(request, 512) {
+                Some(ControlRequest::Info) => {
+                    reply(STATUS_OK, &encode_info(&DiskInfo { block_size: 512, flags: 0, blocks: disk.len() as u64 / 512 }))
+                }
+                Some(ControlRequest::Read { lba, count }) => {
+                    let start = lba as usize * 512;
+                    reply(STATUS_OK, &disk[start..start + count as usize * 512])
+                }
+                Some(ControlRequest::Write { lba, data }) => {
+                    let start = lba as usize * 512;
+                    disk[start..start + data.len()].copy_from_slice(data);
+                    reply(STATUS_OK, &[])
+                }
+                Some(ControlRequest::Flush) => reply(STATUS_OK, &[]),
+                Some(ControlRequest::ReadIntegrity { lba, count, algorithm }) => {
+                    let start = lba as usize * 512;
+                    reply(STATUS_OK, &encode_integrity_read(algorithm, 512, &disk[start..start + count as usize * 512]))
+                }
+                Some(ControlRequest::WriteIntegrity { lba, checksums, data }) => match checksums.verify(data) {
+                    Ok(()) => {
+                        let start = lba as usize * 512;
+                        disk[start..start + data.len()].copy_from_slice(data);
+                        reply(STATUS_OK, &[])
+                    }
+                    Err(_) => reply(STATUS_EBADMSG, &[]),
+                },
+                None => reply(STATUS_EINVAL, &[]),
+            })
+        }
+    }
+
+    #[test]
+    fn test_transfers_are_split_into_whole_blocks() {
+        let mut disk = MemoryDisk(vec![0; 1024 * 512]);
+        let mut client = DiskClient::new(&mut disk);
+        assert_eq!(client.info().unwrap().size(), 512 * 1024);
+
+        let data: Vec<u8> = (0..3 * MAX_TRANSFER / 2).map(|index| (index % 251) as u8).collect();
+        client.write(7, &data).unwrap();
+        let mut back = vec![0; data.len()];
+        client.read(7, &mut back).unwrap();
+        assert_eq!(back, data);
+        assert_eq!(client.write(0, &[1; 100]), Err(STATUS_EINVAL));
+        assert_eq!(client.flush(), Ok(()));
+        assert_eq!(&disk.0[7 * 512..7 * 512 + 4], &data[..4]);
+    }
+
+    #[test]
+    fn test_rejects_partial_and_oversized_transfers() {
+        let mut request = BLK_CTRL_READ.to_le_bytes().to_vec();
+        request.extend_from_slice(&4u64.to_le_bytes());
+        request.extend_from_slice(&((MAX_TRANSFER / 512) as u32 + 1).to_le_bytes());
+        assert_eq!(ControlRequest::decode(&request, 512), None);
+        request.truncate(12);
+        request.extend_from_slice(&2u32.to_le_bytes());
+        assert_eq!(ControlRequest::decode(&request, 512), Some(ControlRequest::Read { lba: 4, count: 2 }));
+
+        let mut request = BLK_CTRL_WRITE.to_le_bytes().to_vec();
+        request.extend_from_slice(&0u64.to_le_bytes());
+        request.extend_from_slice(&[0; 4096]);
+        assert!(ControlRequest::decode(&request, 4096).is_some());
+        assert_eq!(ControlRequest::decode(&request[..request.len() - 1], 4096), None);
+    }
+
+    #[test]
+    fn test_integrity_travels_with_the_data() {
+        let mut disk = MemoryDisk(vec![0; 256 * 512]);
+        let mut client = DiskClient::new(&mut disk);
+        let data: Vec<u8> = (0..MAX_TRANSFER + 1024).map(|index| (index % 241) as u8).collect();
+        client.write_verified(3, &data, Algorithm::Crc32c).unwrap();
+        let mut back = vec![0; data.len()];
+        client.read_verified(3, &mut back, Algorithm::XxHash64).unwrap();
+        assert_eq!(back, data);
+
+        // A block damaged between the checksum and the driver
+        let checksums = Checksums::compute(Algorithm::Crc32c, 512, &data[..1024]);
+        let mut request = BLK_CTRL_WRITE_INTEGRITY.to_le_bytes().to_vec();
+        request.extend_from_slice(&0u64.to_le_bytes());
+        request.extend_from_slice(&2u32.to_le_bytes());
+        request.extend_from_slice(&Algorithm::Crc32c.as_u32().to_le_bytes());
+        request.extend_from_slice(&checksums.encode());
+        request.extend_from_slice(&data[..1024]);
+        request[20 + 8 + 600] ^= 1;
+        assert_eq!((&mut disk).call(&request), Some(STATUS_EBADMSG.to_le_bytes().to_vec()));
+        assert_eq!(ControlRequest::decode(&request[..request.len() - 512], 512), None);
+    }
+}
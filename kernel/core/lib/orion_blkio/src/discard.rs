@@ -0,0 +1,253 @@
+/*
+ * Orion Operating System - Discard Coalescing
+ *
+ * Discards are kept as a set of disjoint extents until the driver sends
+ * them: a new discard that touches or overlaps pending ones is merged with
+ * them, and a write to a pending range carves that part back out so the
+ * late discard cannot destroy the new data. Sending packs the extents into
+ * as few commands as the device limits allow, shrinking each one to whole
+ * deallocation units and splitting those longer than a range may be.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Size of one ATA DATA SET MANAGEMENT payload block
+pub const ATA_TRIM_BLOCK_SIZE: usize = 512;
+/// Size of one NVMe Dataset Management range descriptor
+pub const NVME_DSM_RANGE_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscardRange {
+    pub lba: u64,
+    pub count: u64,
+}
+
+/// Discard limits of a device, in logical blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscardLimits {
+    /// Ranges one command can carry
+    pub max_ranges: usize,
+    /// Blocks one range can cover
+    pub max_range_blocks: u64,
+    /// Blocks one command can cover, 0 for no limit
+    pub max_command_blocks: u64,
+    /// Deallocation unit; discards are shrunk to whole, aligned units
+    pub granularity: u64,
+}
+
+impl DiscardLimits {
+    /// NVMe Dataset Management: 256 ranges of up to 2^32 - 1 blocks
+    pub const NVME_DSM: Self =
+        Self { max_ranges: 256, max_range_blocks: u32::MAX as u64, max_command_blocks: 0, granularity: 1 };
+
+    /// ATA TRIM with a single payload block: 64 entries of up to 65535 sectors
+    pub const ATA_TRIM: Self = Self { max_ranges: 64, max_range_blocks: 0xFFFF, max_command_blocks: 0, granularity: 1 };
+
+    /// Same limits with values the batcher can always make progress with:
+    /// block counts are rounded down to whole units, but never below one
+    fn sanitized(self) -> Self {
+        let granularity = self.granularity.max(1);
+        let round = |blocks: u64| (blocks / granularity).max(1) * granularity;
+        Self {
+            max_ranges: self.max_ranges.max(1),
+            max_range_blocks: round(self.max_range_blocks),
+            max_command_blocks: if self.max_command_blocks == 0 { 0 } else { round(self.max_command_blocks) },
+            granularity,
+        }
+    }
+}
+
+pub struct DiscardBatcher {
+    limits: DiscardLimits,
+    /// Pending extents, start -> end (exclusive), never touching each other
+    pending: BTreeMap<u64, u64>,
+    blocks: u64,
+}
+
+impl DiscardBatcher {
+    pub fn new(limits: DiscardLimits) -> Self {
+        Self { limits: limits.sanitized(), pending: BTreeMap::new(), blocks: 0 }
+    }
+
+    pub fn limits(&self) -> &DiscardLimits {
+        &self.limits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Blocks waiting to be discarded
+    pub fn pending_blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// A full command's worth of ranges is pending
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.limits.max_ranges
+    }
+
+    /// Queue a discard of `count` blocks at `lba`
+    pub fn add(&mut self, lba: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let mut start = lba;
+        let mut end = lba.saturating_add(count);
+        if let Some((&first, &last)) = self.pending.range(..=start).next_back() {
+            if last >= start {
+                start = first;
+                end = end.max(last);
+                self.remove(first);
+            }
+        }
+        while let Some((&first, &last)) = self.pending.range(start..=end).next() {
+            end = end.max(last);
+            self.remove(first);
+        }
+        self.insert(start, end);
+    }
+
+    /// Drop the part of pending discards that a write of `count` blocks
+    /// at `lba` is about to overwrite
+    pub fn cancel(&mut self, lba: u64, count: u64) {
+        let end = lba.saturating_add(count);
+        let overlapping: Vec<(u64, u64)> =
+            self.pending.range(..end).rev().take_while(|(_, &last)| last > lba).map(|(&s, &e)| (s, e)).collect();
+        for (first, last) in overlapping {
+            self.remove(first);
+            if first < lba {
+                self.insert(first, lba);
+            }
+            if last > end {
+                self.insert(end, last);
+            }
+        }
+    }
+
+    /// Take every pending discard, packed into commands that fit the limits.
+    /// Extents smaller than a deallocation unit are dropped: the device
+    /// would ignore them anyway.
+    pub fn take_commands(&mut self) -> Vec<Vec<DiscardRange>> {
+        let limits = self.limits;
+        let granularity = limits.granularity;
+        let mut commands = Vec::new();
+        let mut current = Vec::new();
+        let mut current_blocks = 0;
+
+        for (start, end) in core::mem::take(&mut self.pending) {
+            let mut lba = start.div_ceil(granularity) * granularity;
+            let end = end / granularity * granularity;
+            while lba < end {
+                let mut count = (end - lba).min(limits.max_range_blocks);
+                if limits.max_command_blocks != 0 {
+                    count = count.min(limits.max_command_blocks - current_blocks);
+                }
+                current.push(DiscardRange { lba, count });
+                current_blocks += count;
+                lba += count;
+                if current.len() == limits.max_ranges || current_blocks == limits.max_command_blocks {
+                    commands.push(core::mem::take(&mut current));
+                    current_blocks = 0;
+                }
+            }
+        }
+        if !current.is_empty() {
+            commands.push(current);
+        }
+        self.blocks = 0;
+        commands
+    }
+
+    fn insert(&mut self, start: u64, end: u64) {
+        self.pending.insert(start, end);
+        self.blocks += end - start;
+    }
+
+    fn remove(&mut self, start: u64) {
+        if let Some(end) = self.pending.remove(&start) {
+            self.blocks -= end - start;
+        }
+    }
+}
+
+/// Range list of an NVMe Dataset Management command
+pub fn encode_nvme_dsm(ranges: &[DiscardRange]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(ranges.len() * NVME_DSM_RANGE_SIZE);
+    for range in ranges {
+        out.extend_from_slice(&0u32.to_le_bytes()); // context attributes
+        out.extend_from_slice(&(range.count as u32).to_le_bytes());
+        out.extend_from_slice(&range.lba.to_le_bytes());
+    }
+    out
+}
+
+/// Payload of an ATA TRIM: 48-bit LBA and 16-bit count per entry, padded
+/// with empty entries to whole 512-byte blocks
+pub fn encode_ata_trim(ranges: &[DiscardRange]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(ranges.len().div_ceil(64).max(1) * ATA_TRIM_BLOCK_SIZE);
+    for range in ranges {
+        let entry = (range.lba & 0xFFFF_FFFF_FFFF) | (range.count << 48);
+        out.extend_from_slice(&entry.to_le_bytes());
+    }
+    out.resize(out.len().div_ceil(ATA_TRIM_BLOCK_SIZE).max(1) * ATA_TRIM_BLOCK_SIZE, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn range(lba: u64, count: u64) -> DiscardRange {
+        DiscardRange { lba, count }
+    }
+
+    #[test]
+    fn test_adjacent_and_overlapping_ranges_merge() {
+        let mut batcher = DiscardBatcher::new(DiscardLimits::NVME_DSM);
+        batcher.add(100, 10);
+        batcher.add(120, 10);
+        batcher.add(110, 10); // bridges the two
+        batcher.add(125, 20); // overlaps the tail
+        batcher.add(500, 8);
+        assert_eq!(batcher.pending_blocks(), 53);
+
+        // A write carves its blocks out of the pending discards
+        batcher.cancel(130, 2);
+        batcher.cancel(498, 4);
+        assert_eq!(batcher.pending_blocks(), 49);
+        assert_eq!(batcher.take_commands(), vec![vec![range(100, 30), range(132, 13), range(502, 6)]]);
+        assert!(batcher.is_empty());
+    }
+
+    #[test]
+    fn test_commands_respect_device_limits() {
+        let limits = DiscardLimits { max_ranges: 2, max_range_blocks: 100, max_command_blocks: 150, granularity: 8 };
+        let mut batcher = DiscardBatcher::new(limits);
+        batcher.add(3, 250); // aligned to [8, 248)
+        batcher.add(1000, 5); // smaller than a unit
+        assert_eq!(batcher.take_commands(), vec![vec![range(8, 96), range(104, 48)], vec![range(152, 96)]]);
+    }
+
+    #[test]
+    fn test_payload_encoding() {
+        let ranges = [range(0x1234, 8), range(1 << 40, 0xFFFF)];
+        let dsm = encode_nvme_dsm(&ranges);
+        assert_eq!(dsm.len(), 32);
+        assert_eq!(&dsm[4..8], &8u32.to_le_bytes());
+        assert_eq!(&dsm[8..16], &0x1234u64.to_le_bytes());
+
+        let trim = encode_ata_trim(&ranges);
+        assert_eq!(trim.len(), ATA_TRIM_BLOCK_SIZE);
+        assert_eq!(&trim[8..16], &((1u64 << 40) | (0xFFFF << 48)).to_le_bytes());
+        assert!(trim[16..].iter().all(|&byte| byte == 0));
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * Orion Operating System - Durability Contract
+ *
+ * What every block driver promises about the requests it completes,
+ * whatever cache sits underneath:
+ *
+ *   FLUSH     when a flush completes, every write that completed before
+ *             the flush was submitted is durable
+ *   FUA       a write carrying REQ_FUA is durable when it completes
+ *   PREFLUSH  a request carrying REQ_PREFLUSH starts only once a flush
+ *             did, so it implies a FLUSH submitted with it
+ *   ordering  no request submitted after a FLUSH or a PREFLUSH request
+ *             starts before that flush completed; requests are not
+ *             moved across one by any queue or scheduler in between
+ *
+ * Nothing else is promised: a completed write without FUA may be lost
+ * until the next flush completes, and writes in flight may land in any
+ * order. BarrierQueue (barrier.rs) keeps the contract on devices with a
+ * volatile write cache; drivers that bypass it must keep it themselves.
+ *
+ * A DurabilityOracle follows the completions a driver reports and tells
+ * what has to survive a power failure at that point, so a simulated or
+ * real device can be checked after a crash. Writes are identified by a
+ * generation, increasing with submission, and a block may legitimately
+ * hold a newer generation than required, never an older one.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Block found older than the contract allows after a crash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub block: u64,
+    /// Generation the block held, 0 if it was never written
+    pub found: u64,
+    /// Oldest generation it may hold
+    pub required: u64,
+}
+
+/// A completed write not known to be durable yet
+struct Unflushed {
+    /// Completion order
+    sequence: u64,
+    block: u64,
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct DurabilityOracle {
+    /// Oldest generation each block may hold after a crash
+    durable: BTreeMap<u64, u64>,
+    unflushed: Vec<Unflushed>,
+    /// Submitted flushes, by token: completions before their submission
+    flushes: BTreeMap<u64, u64>,
+    completions: u64,
+    next_token: u64,
+}
+
+impl DurabilityOracle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn make_durable(&mut self, block: u64, generation: u64) {
+        let required = self.durable.entry(block).or_insert(0);
+        *required = (*required).max(generation);
+    }
+
+    /// Write of `generation` to `block` completed successfully, with
+    /// REQ_FUA if `fua`
+    pub fn write_completed(&mut self, block: u64, generation: u64, fua: bool) {
+        self.completions += 1;
+        if fua {
+            self.make_durable(block, generation);
+        } else {
+            self.unflushed.push(Unflushed { sequence: self.completions, block, generation });
+        }
+    }
+
+    /// A FLUSH or PREFLUSH request was submitted; the token is passed to
+    /// flush_completed once it succeeded
+    pub fn flush_submitted(&mut self) -> u64 {
+        self.next_token += 1;
+        self.flushes.insert(self.next_token, self.completions);
+        self.next_token
+    }
+
+    pub fn flush_completed(&mut self, token: u64) {
+        let Some(mark) = self.flushes.remove(&token) else {
+            return;
+        };
+        let (flushed, unflushed) = self.unflushed.drain(..).partition(|write| write.sequence <= mark);
+        self.unflushed = unflushed;
+        for write in flushed.iter() {
+            self.make_durable(write.block, write.generation);
+        }
+    }
+
+    /// A flush that failed promises nothing
+    pub fn flush_failed(&mut self, token: u64) {
+        self.flushes.remove(&token);
+    }
+
+    /// Oldest generation `block` may hold after a crash now, 0 for any
+    pub fn required(&self, block: u64) -> u64 {
+        self.durable.get(&block).copied().unwrap_or(0)
+    }
+
+    /// Compare what survived a crash, the generation of each block given
+    /// by `survived`, with what was promised
+    pub fn check(&self, survived: impl Fn(u64) -> u64) -> Vec<Violation> {
+        self.durable
+            .iter()
+            .map(|(&block, &required)| Violation { block, found: survived(block), required })
+            .filter(|violation| violation.found < violation.required)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_flush_covers_writes_completed_before_its_submission() {
+        let mut oracle = DurabilityOracle::new();
+        oracle.write_completed(1, 1, false);
+        let flush = oracle.flush_submitted();
+        // Completed while the flush runs: not covered by it
+        oracle.write_completed(2, 2, false);
+        oracle.write_completed(3, 3, true);
+        assert_eq!((oracle.required(1), oracle.required(3)), (0, 3));
+
+        oracle.flush_completed(flush);
+        assert_eq!((oracle.required(1), oracle.required(2)), (1, 0));
+        assert_eq!(
+            oracle.check(|block| if block == 3 { 4 } else { 0 }),
+            vec![Violation { block: 1, found: 0, required: 1 }]
+        );
+
+        let failed = oracle.flush_submitted();
+        oracle.flush_failed(failed);
+        oracle.flush_completed(failed);
+        assert_eq!(oracle.required(2), 0);
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * Orion Operating System - End-to-End Data Integrity
+ *
+ * A checksum per block, computed where the data is produced (the file
+ * system for a write, the driver for a read) and carried with it down or
+ * up the stack. Each layer the data crosses - cache, scheduler, volume
+ * manager, driver - can verify it against the checksums, so a buffer corrupted by a stray
+ * DMA or bad memory is caught at the boundary it was damaged behind
+ * rather than written to disk or handed to an application.
+ *
+ * Two algorithms: CRC32C, which processors accelerate and storage
+ * protocols already use, and xxHash64, faster in software and with a
+ * wider tag. Hashing is not free, so the stack keeps the time spent on it
+ * next to the time spent on I/O and reports the overhead it costs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Crc32c = 1,
+    XxHash64 = 2,
+}
+
+impl Algorithm {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            1 => Some(Algorithm::Crc32c),
+            2 => Some(Algorithm::XxHash64),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// Bytes of one checksum on the wire
+    pub fn tag_size(self) -> usize {
+        match self {
+            Algorithm::Crc32c => 4,
+            Algorithm::XxHash64 => 8,
+        }
+    }
+
+    pub fn hash(self, data: &[u8]) -> u64 {
+        match self {
+            Algorithm::Crc32c => crc32c(data) as u64,
+            Algorithm::XxHash64 => xxhash64(data, 0),
+        }
+    }
+}
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            // Castagnoli polynomial, reflected
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82F6_3B78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+pub fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| CRC32C_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn le64(data: &[u8]) -> u64 {
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(&data[..8]);
+    u64::from_le_bytes(raw)
+}
+
+fn xxh_round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2)).rotate_left(31).wrapping_mul(PRIME64_1)
+}
+
+fn xxh_merge(acc: u64, value: u64) -> u64 {
+    (acc ^ xxh_round(0, value)).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4)
+}
+
+pub fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    let mut rest = data;
+    let mut hash = if data.len() >= 32 {
+        let mut lanes = [
+            seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+            seed.wrapping_add(PRIME64_2),
+            seed,
+            seed.wrapping_sub(PRIME64_1),
+        ];
+        while rest.len() >= 32 {
+            for (lane, word) in lanes.iter_mut().zip(rest[..32].chunks_exact(8)) {
+                *lane = xxh_round(*lane, le64(word));
+            }
+            rest = &rest[32..];
+        }
+        let hash = lanes[0]
+            .rotate_left(1)
+            .wrapping_add(lanes[1].rotate_left(7))
+            .wrapping_add(lanes[2].rotate_left(12))
+            .wrapping_add(lanes[3].rotate_left(18));
+        lanes.iter().fold(hash, |hash, &lane| xxh_merge(hash, lane))
+    } else {
+        seed.wrapping_add(PRIME64_5)
+    };
+    hash = hash.wrapping_add(data.len() as u64);
+
+    while rest.len() >= 8 {
+        hash = (hash ^ xxh_round(0, le64(rest))).rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+        rest = &rest[8..];
+    }
+    if rest.len() >= 4 {
+        let word = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as u64;
+        hash = (hash ^ word.wrapping_mul(PRIME64_1)).rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+        rest = &rest[4..];
+    }
+    for &byte in rest {
+        hash = (hash ^ (byte as u64).wrapping_mul(PRIME64_5)).rotate_left(11).wrapping_mul(PRIME64_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME64_3);
+    hash ^ (hash >> 32)
+}
+
+/// Layer of the stack a buffer is verified at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    Filesystem,
+    Cache,
+    Scheduler,
+    Volume,
+    Driver,
+}
+
+/// The data no longer matches its checksums
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// First bad block, counted from the start of the buffer
+    pub block: usize,
+}
+
+/// Checksums of a buffer of whole blocks, one per block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksums {
+    pub algorithm: Algorithm,
+    pub block_size: usize,
+    pub tags: Vec<u64>,
+}
+
+impl Checksums {
+    pub fn compute(algorithm: Algorithm, block_size: usize, data: &[u8]) -> Self {
+        let block_size = block_size.max(1);
+        let tags = data.chunks(block_size).map(|block| algorithm.hash(block)).collect();
+        Self { algorithm, block_size, tags }
+    }
+
+    /// Check `data` block by block. A buffer of another length than the
+    /// checksums cover fails at the first block missing or in excess.
+    pub fn verify(&self, data: &[u8]) -> Result<(), Mismatch> {
+        for (block, chunk) in data.chunks(self.block_size).enumerate() {
+            if self.tags.get(block) != Some(&self.algorithm.hash(chunk)) {
+                return Err(Mismatch { block });
+            }
+        }
+        let blocks = data.len().div_ceil(self.block_size);
+        if blocks != self.tags.len() {
+            return Err(Mismatch { block: blocks.min(self.tags.len()) });
+        }
+        Ok(())
+    }
+
+    /// The tags back to back, each `tag_size` bytes little-endian
+    pub fn encode(&self) -> Vec<u8> {
+        let size = self.algorithm.tag_size();
+        let mut out = Vec::with_capacity(self.tags.len() * size);
+        for tag in &self.tags {
+            out.extend_from_slice(&tag.to_le_bytes()[..size]);
+        }
+        out
+    }
+
+    /// Read `count` tags from the front of `data`
+    pub fn decode(algorithm: Algorithm, block_size: usize, count: usize, data: &[u8]) -> Option<Self> {
+        let size = algorithm.tag_size();
+        let raw = data.get(..count.checked_mul(size)?)?;
+        let tags = raw
+            .chunks_exact(size)
+            .map(|bytes| {
+                let mut tag = [0u8; 8];
+                tag[..size].copy_from_slice(bytes);
+                u64::from_le_bytes(tag)
+            })
+            .collect();
+        Some(Self { algorithm, block_size: block_size.max(1), tags })
+    }
+}
+
+/// What integrity checking found and what it cost
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IntegrityStats {
+    /// Blocks checked against their checksum
+    pub verified: u64,
+    /// Blocks checksummed for data coming up from the device
+    pub computed: u64,
+    /// Mismatches, per Boundary in declaration order
+    pub mismatches: [u64; 5],
+    /// Where the last mismatch was seen and on which block
+    pub last_mismatch: Option<(Boundary, u64)>,
+    pub hashed_bytes: u64,
+    pub hash_ns: u64,
+    /// Time the requests took end to end, hashing included
+    pub io_ns: u64,
+}
+
+impl IntegrityStats {
+    pub fn record_verify(&mut self, blocks: usize, bytes: usize, ns: u64) {
+        self.verified += blocks as u64;
+        self.hashed_bytes += bytes as u64;
+        self.hash_ns += ns;
+    }
+
+    pub fn record_compute(&mut self, blocks: usize, bytes: usize, ns: u64) {
+        self.computed += blocks as u64;
+        self.hashed_bytes += bytes as u64;
+        self.hash_ns += ns;
+    }
+
+    pub fn record_mismatch(&mut self, boundary: Boundary, block: u64) {
+        self.mismatches[boundary as usize] += 1;
+        self.last_mismatch = Some((boundary, block));
+    }
+
+    pub fn record_io(&mut self, ns: u64) {
+        self.io_ns += ns;
+    }
+
+    pub fn total_mismatches(&self) -> u64 {
+        self.mismatches.iter().sum()
+    }
+
+    /// Share of the I/O time spent hashing, in parts per million
+    pub fn overhead_ppm(&self) -> u64 {
+        if self.io_ns == 0 {
+            return 0;
+        }
+        (self.hash_ns as u128 * 1_000_000 / self.io_ns as u128).min(1_000_000) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_known_answers() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(&[0; 32]), 0x8A91_36AA);
+        assert_eq!(xxhash64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxhash64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(xxhash64(b"Nobody inspects the spammish repetition", 0), 0xFBCE_A83C_8A37_8BF1);
+    }
+
+    #[test]
+    fn test_finds_the_damaged_block() {
+        for algorithm in [Algorithm::Crc32c, Algorithm::XxHash64] {
+            let mut data: Vec<u8> = (0..4 * 512).map(|index| (index % 253) as u8).collect();
+            let checksums = Checksums::compute(algorithm, 512, &data);
+            assert_eq!(checksums.verify(&data), Ok(()));
+
+            let wire = checksums.encode();
+            assert_eq!(wire.len(), 4 * algorithm.tag_size());
+            assert_eq!(Checksums::decode(algorithm, 512, 4, &wire), Some(checksums.clone()));
+            assert_eq!(Checksums::decode(algorithm, 512, 5, &wire), None);
+
+            data[2 * 512 + 17] ^= 0x10;
+            assert_eq!(checksums.verify(&data), Err(Mismatch { block: 2 }));
+            assert_eq!(checksums.verify(&data[..512]), Err(Mismatch { block: 1 }));
+            assert_eq!(checksums.verify(&vec![0; 5 * 512]), Err(Mismatch { block: 0 }));
+        }
+    }
+
+    #[test]
+    fn test_reports_overhead() {
+        let mut stats = IntegrityStats::default();
+        assert_eq!(stats.overhead_ppm(), 0);
+        stats.record_verify(8, 4096, 300);
+        stats.record_compute(8, 4096, 200);
+        stats.record_io(100_000);
+        stats.record_mismatch(Boundary::Scheduler, 42);
+        assert_eq!(stats.overhead_ppm(), 5_000);
+        assert_eq!(stats.hashed_bytes, 8192);
+        assert_eq!(stats.total_mismatches(), 1);
+        assert_eq!(stats.last_mismatch, Some((Boundary::Scheduler, 42)));
+    }
+}
@@ -0,0 +1,43 @@
+/*
+ * Orion Operating System - Block I/O Ordering
+ *
+ * Shared request handling for block drivers that talk to real SSDs.
+ * DiscardBatcher collects discards, merges adjacent and overlapping ranges
+ * and packs them into device commands within the limits the device
+ * reported, so a filesystem freeing thousands of small extents costs a few
+ * DSM or TRIM commands instead of thousands. BarrierQueue orders requests
+ * around FLUSH and FUA: a flush only goes out once the writes before it
+ * completed and holds back everything behind it, and FUA is emulated with
+ * a trailing flush on devices without it, which is what journaling
+ * filesystems need for a commit record to mean anything; the contract
+ * every driver keeps is spelled out in durability.rs, with an oracle to
+ * check a device against it after a crash. A Watchdog puts
+ * a deadline on every command and escalates from aborting it to resetting
+ * the controller to giving the device up. Per-block checksums let a
+ * write or read be verified at every layer it crosses. The control
+ * requests give tools such as the installer raw access to a whole disk.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod barrier;
+pub mod control;
+pub mod discard;
+pub mod durability;
+pub mod integrity;
+pub mod timeout;
+
+pub use barrier::{BarrierQueue, Command, DeviceCache, IoOp, IoStatus, Request, REQ_FUA, REQ_PREFLUSH};
+pub use control::{ControlRequest, DiskClient, DiskInfo, Transport, BLK_IOCTL_CONTROL};
+pub use discard::{encode_ata_trim, encode_nvme_dsm, DiscardBatcher, DiscardLimits, DiscardRange};
+pub use durability::{DurabilityOracle, Violation};
+pub use integrity::{Algorithm, Boundary, Checksums, IntegrityStats};
+pub use timeout::{Recovery, TimeoutPolicy, Watchdog};
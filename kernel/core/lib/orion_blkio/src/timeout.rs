@@ -0,0 +1,234 @@
+/*
+ * Orion Operating System - Command Timeouts
+ *
+ * A Watchdog gives every command a driver issues a deadline and decides
+ * what to do once it passes. Recovery escalates in three steps:
+ *
+ *   - the command is aborted with whatever the driver has for it (NVMe
+ *     Abort, a port reset on AHCI, a reconnect for a network disk) and
+ *     given a shorter deadline to come back
+ *   - if the abort fails or the command still does not complete, the
+ *     whole controller is reset, which loses every command in flight
+ *   - after too many resets in a row the device is taken offline and
+ *     every command fails at once instead of waiting again
+ *
+ * A command that completes after being aborted is reported as timed out
+ * whatever status the device gave it, so callers can tell a device that
+ * stopped answering from one that reported a media error.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// Deadlines of the recovery steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutPolicy {
+    /// Time a command may take before it is aborted
+    pub command_ns: u64,
+    /// Time an aborted command has to come back before the reset
+    pub abort_ns: u64,
+    /// Controller resets in a row, without a command completing in
+    /// between, before the device is given up
+    pub max_resets: u32,
+}
+
+impl TimeoutPolicy {
+    pub const DEFAULT: Self = Self { command_ns: 30 * NS_PER_SEC, abort_ns: 5 * NS_PER_SEC, max_resets: 3 };
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Step the driver has to take now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Abort command `id`; report a failed abort with `abort_failed`
+    Abort(u64),
+    /// Reset the controller and report the outcome with `reset_done`
+    Reset,
+    /// Stop using the device and fail the commands from `abandon`
+    Offline,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Timer {
+    deadline: u64,
+    aborted: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    policy: TimeoutPolicy,
+    timers: BTreeMap<u64, Timer>,
+    resets: u32,
+    resetting: bool,
+    offline: bool,
+}
+
+impl Watchdog {
+    pub fn new(policy: TimeoutPolicy) -> Self {
+        Self { policy, ..Self::default() }
+    }
+
+    pub fn policy(&self) -> &TimeoutPolicy {
+        &self.policy
+    }
+
+    /// Command `id` was issued at `now`
+    pub fn start(&mut self, id: u64, now: u64) {
+        let deadline = now.saturating_add(self.policy.command_ns);
+        self.timers.insert(id, Timer { deadline, aborted: false });
+    }
+
+    /// Command `id` completed. True when it had timed out, in which case
+    /// it is reported as such whatever the device said.
+    pub fn finish(&mut self, id: u64) -> bool {
+        match self.timers.remove(&id) {
+            Some(timer) if timer.aborted => true,
+            Some(_) => {
+                // The device answers again
+                self.resets = 0;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Next recovery step, if a deadline has passed at `now`
+    pub fn poll(&mut self, now: u64) -> Option<Recovery> {
+        if self.offline || self.resetting {
+            return None;
+        }
+        let (&id, timer) = self.timers.iter_mut().find(|(_, timer)| now >= timer.deadline)?;
+        if !timer.aborted {
+            timer.aborted = true;
+            timer.deadline = now.saturating_add(self.policy.abort_ns);
+            return Some(Recovery::Abort(id));
+        }
+        Some(self.escalate())
+    }
+
+    /// Command `id` is known to have timed out, by a timer of the
+    /// driver's own; the next `poll` aborts it
+    pub fn expire(&mut self, id: u64) {
+        if let Some(timer) = self.timers.get_mut(&id) {
+            timer.deadline = 0;
+        }
+    }
+
+    /// The abort of command `id` could not be issued or was refused, so
+    /// there is no point in waiting for it
+    pub fn abort_failed(&mut self, id: u64) {
+        if let Some(timer) = self.timers.get_mut(&id) {
+            timer.aborted = true;
+            timer.deadline = 0;
+        }
+    }
+
+    fn escalate(&mut self) -> Recovery {
+        if self.resets >= self.policy.max_resets {
+            self.offline = true;
+            return Recovery::Offline;
+        }
+        self.resets += 1;
+        self.resetting = true;
+        Recovery::Reset
+    }
+
+    /// The reset asked for by `poll` is over. Returns the commands it
+    /// lost, which have timed out; a failed reset takes the device offline.
+    pub fn reset_done(&mut self, ok: bool) -> Vec<u64> {
+        self.resetting = false;
+        if !ok {
+            self.offline = true;
+        }
+        self.abandon()
+    }
+
+    /// Forget every command in flight and return them
+    pub fn abandon(&mut self) -> Vec<u64> {
+        core::mem::take(&mut self.timers).into_keys().collect()
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Put an offline device back in service, e.g. after it was replaced
+    pub fn revive(&mut self) {
+        self.offline = false;
+        self.resets = 0;
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.timers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const POLICY: TimeoutPolicy = TimeoutPolicy { command_ns: 100, abort_ns: 10, max_resets: 1 };
+
+    #[test]
+    fn test_aborts_then_resets_then_gives_up() {
+        let mut watchdog = Watchdog::new(POLICY);
+        watchdog.start(1, 0);
+        watchdog.start(2, 50);
+        assert_eq!(watchdog.poll(99), None);
+        assert_eq!(watchdog.poll(100), Some(Recovery::Abort(1)));
+        assert_eq!(watchdog.poll(105), None);
+        // The abort did not bring command 1 back
+        assert_eq!(watchdog.poll(110), Some(Recovery::Reset));
+        assert_eq!(watchdog.poll(200), None);
+        assert_eq!(watchdog.reset_done(true), vec![1, 2]);
+        assert!(!watchdog.is_offline());
+
+        watchdog.start(3, 300);
+        assert_eq!(watchdog.poll(400), Some(Recovery::Abort(3)));
+        watchdog.abort_failed(3);
+        // One reset already, nothing completed since
+        assert_eq!(watchdog.poll(401), Some(Recovery::Offline));
+        assert!(watchdog.is_offline());
+        assert_eq!(watchdog.poll(500), None);
+        assert_eq!(watchdog.abandon(), vec![3]);
+    }
+
+    #[test]
+    fn test_aborted_commands_complete_as_timed_out() {
+        let mut watchdog = Watchdog::new(POLICY);
+        watchdog.start(1, 0);
+        watchdog.start(2, 0);
+        assert!(!watchdog.finish(2));
+        watchdog.expire(1);
+        assert_eq!(watchdog.poll(1), Some(Recovery::Abort(1)));
+        assert!(watchdog.finish(1));
+        assert_eq!(watchdog.in_flight(), 0);
+
+        // A completion resets the count of resets in a row
+        watchdog.start(3, 0);
+        watchdog.abort_failed(3);
+        assert_eq!(watchdog.poll(0), Some(Recovery::Reset));
+        watchdog.reset_done(true);
+        watchdog.start(4, 0);
+        assert!(!watchdog.finish(4));
+        watchdog.start(5, 0);
+        watchdog.abort_failed(5);
+        assert_eq!(watchdog.poll(0), Some(Recovery::Reset));
+        assert_eq!(watchdog.reset_done(false), vec![5]);
+        assert!(watchdog.is_offline());
+    }
+}
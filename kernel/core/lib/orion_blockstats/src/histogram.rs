@@ -0,0 +1,253 @@
+/*
+ * Orion Operating System - Latency Histograms
+ *
+ * HDR-style log-linear histogram of nanosecond latencies. Values below
+ * 2 * SUB_BUCKETS are counted exactly; above that every power of two is
+ * split into SUB_BUCKETS equal buckets, so any recorded value is known to
+ * within 1 / SUB_BUCKETS (about 3%) whatever its magnitude. Counts are
+ * atomics so completions can be recorded from any context through a
+ * shared reference; queries work on a snapshot.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+const SUB_BUCKET_BITS: u32 = 5;
+
+/// Buckets per power of two
+pub const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+/// Latencies at or above this (about 68.7 s) are counted in the last bucket
+pub const MAX_TRACKABLE: u64 = 1 << 36;
+
+/// 2 * SUB_BUCKETS exact buckets, then SUB_BUCKETS per power of two
+pub const BUCKETS: usize = (2 * SUB_BUCKETS + (36 - SUB_BUCKET_BITS - 1) as u64 * SUB_BUCKETS) as usize;
+
+fn bucket_of(value: u64) -> usize {
+    let value = value.min(MAX_TRACKABLE - 1);
+    if value < 2 * SUB_BUCKETS {
+        return value as usize;
+    }
+    let shift = 63 - value.leading_zeros() - SUB_BUCKET_BITS;
+    let mantissa = value >> shift;
+    (2 * SUB_BUCKETS + (shift as u64 - 1) * SUB_BUCKETS + mantissa - SUB_BUCKETS) as usize
+}
+
+/// Lowest and highest value counted in a bucket
+fn bucket_range(bucket: usize) -> (u64, u64) {
+    let bucket = bucket as u64;
+    if bucket < 2 * SUB_BUCKETS {
+        return (bucket, bucket);
+    }
+    let shift = (bucket - 2 * SUB_BUCKETS) / SUB_BUCKETS + 1;
+    let mantissa = (bucket - 2 * SUB_BUCKETS) % SUB_BUCKETS + SUB_BUCKETS;
+    let low = mantissa << shift;
+    (low, low + (1 << shift) - 1)
+}
+
+pub struct LatencyHistogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency_ns: u64) {
+        self.buckets[bucket_of(latency_ns)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(latency_ns, Ordering::Relaxed);
+        self.min.fetch_min(latency_ns, Ordering::Relaxed);
+        self.max.fetch_max(latency_ns, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Zero the histogram. Not atomic as a whole: values recorded while it
+    /// runs may be partly kept.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.min.store(u64::MAX, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let buckets: Vec<u64> = self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect();
+        HistogramSnapshot {
+            // Derived from the buckets so that it matches them exactly
+            count: buckets.iter().sum(),
+            buckets,
+            sum: self.sum.load(Ordering::Relaxed),
+            min: self.min.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Latency distribution in nanoseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub min: u64,
+    pub mean: u64,
+    pub max: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+}
+
+/// Point-in-time copy of a histogram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for HistogramSnapshot {
+    fn default() -> Self {
+        Self { buckets: vec![0; BUCKETS], count: 0, sum: 0, min: u64::MAX, max: 0 }
+    }
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> u64 {
+        if self.count == 0 {
+            0
+        } else {
+            self.min
+        }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> u64 {
+        self.sum.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// Add the values of another snapshot
+    pub fn merge(&mut self, other: &HistogramSnapshot) {
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.sum = self.sum.wrapping_add(other.sum);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Smallest latency that `per_million` millionths of the values do not
+    /// exceed, to the histogram's precision: 500_000 is the median, 999_000
+    /// the 99.9th percentile. Zero when empty.
+    pub fn percentile(&self, per_million: u32) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let per_million = per_million.min(1_000_000) as u128;
+        let rank = ((self.count as u128 * per_million).div_ceil(1_000_000) as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                // The bucket's top, but never beyond what was recorded
+                return bucket_range(bucket).1.min(self.max).max(self.min());
+            }
+        }
+        self.max
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count,
+            min: self.min(),
+            mean: self.mean(),
+            max: self.max,
+            p50: self.percentile(500_000),
+            p90: self.percentile(900_000),
+            p99: self.percentile(990_000),
+            p999: self.percentile(999_000),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_are_contiguous() {
+        let mut expected_low = 0;
+        for bucket in 0..BUCKETS {
+            let (low, high) = bucket_range(bucket);
+            assert_eq!(low, expected_low);
+            assert_eq!((bucket_of(low), bucket_of(high)), (bucket, bucket));
+            // Relative precision holds everywhere
+            assert!((high - low) * SUB_BUCKETS <= low.max(1));
+            expected_low = high + 1;
+        }
+        assert_eq!(expected_low, MAX_TRACKABLE);
+        assert_eq!(bucket_of(u64::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_reports_percentiles() {
+        let histogram = LatencyHistogram::new();
+        // 1..=1000 microseconds
+        for micros in 1..=1000u64 {
+            histogram.record(micros * 1000);
+        }
+        let summary = histogram.snapshot().summary();
+        assert_eq!((summary.count, summary.min, summary.max, summary.mean), (1000, 1000, 1_000_000, 500_500));
+        for (value, exact) in [(summary.p50, 500_000), (summary.p99, 990_000), (summary.p999, 999_000)] {
+            assert!(value >= exact && value - exact <= exact / SUB_BUCKETS, "{value} {exact}");
+        }
+        assert_eq!(histogram.snapshot().percentile(1_000_000), 1_000_000);
+
+        let mut merged = HistogramSnapshot::default();
+        merged.merge(&histogram.snapshot());
+        merged.merge(&histogram.snapshot());
+        assert_eq!((merged.count(), merged.mean(), merged.min()), (2000, 500_500, 1000));
+
+        histogram.reset();
+        assert_eq!(histogram.snapshot().summary(), LatencySummary::default());
+    }
+}
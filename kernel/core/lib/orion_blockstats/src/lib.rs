@@ -0,0 +1,24 @@
+/*
+ * Orion Operating System - Block Statistics
+ *
+ * Runtime statistics for block drivers: HDR-style latency histograms per
+ * operation type with percentile queries, lifetime counters, interval
+ * throughput and reset, behind one BlockStatsSource trait that NBD, NVMe
+ * and AHCI implement alike.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod histogram;
+pub mod stats;
+
+pub use histogram::{HistogramSnapshot, LatencyHistogram, LatencySummary};
+pub use stats::{BlockStatistics, BlockStatsReport, BlockStatsSource, Operation, OperationReport, Throughput};
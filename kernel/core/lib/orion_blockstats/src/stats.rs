@@ -0,0 +1,269 @@
+/*
+ * Orion Operating System - Block Device Statistics
+ *
+ * Per-operation accounting shared by the block drivers: a latency
+ * histogram, operation, byte and error counters for reads, writes,
+ * flushes, trims and everything else. Throughput is computed over
+ * intervals delimited by successive throughput() calls, so a monitor
+ * polling every second gets per-second rates rather than lifetime
+ * averages.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::histogram::{HistogramSnapshot, LatencyHistogram, LatencySummary};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Write,
+    Flush,
+    Trim,
+    Other,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] =
+        [Operation::Read, Operation::Write, Operation::Flush, Operation::Trim, Operation::Other];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Default)]
+struct OperationStats {
+    latency: LatencyHistogram,
+    operations: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    // Totals at the start of the current throughput interval
+    interval_operations: AtomicU64,
+    interval_bytes: AtomicU64,
+}
+
+/// Lifetime figures of one operation type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationReport {
+    pub operations: u64,
+    pub bytes: u64,
+    pub errors: u64,
+    pub latency: LatencySummary,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockStatsReport {
+    pub read: OperationReport,
+    pub write: OperationReport,
+    pub flush: OperationReport,
+    pub trim: OperationReport,
+    pub other: OperationReport,
+    /// Latency over all operations
+    pub latency: LatencySummary,
+}
+
+impl BlockStatsReport {
+    pub fn operation(&self, operation: Operation) -> &OperationReport {
+        match operation {
+            Operation::Read => &self.read,
+            Operation::Write => &self.write,
+            Operation::Flush => &self.flush,
+            Operation::Trim => &self.trim,
+            Operation::Other => &self.other,
+        }
+    }
+
+    pub fn total_operations(&self) -> u64 {
+        Operation::ALL.iter().map(|&operation| self.operation(operation).operations).sum()
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        Operation::ALL.iter().map(|&operation| self.operation(operation).errors).sum()
+    }
+}
+
+/// Rates over one interval, per second
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Throughput {
+    pub interval_ns: u64,
+    pub read_iops: u64,
+    pub write_iops: u64,
+    /// Flushes, trims and other operations
+    pub other_iops: u64,
+    pub read_bytes_per_sec: u64,
+    pub write_bytes_per_sec: u64,
+}
+
+pub struct BlockStatistics {
+    operations: [OperationStats; 5],
+    interval_start: AtomicU64,
+}
+
+impl Default for BlockStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for BlockStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockStatistics").field("report", &self.report()).finish()
+    }
+}
+
+impl BlockStatistics {
+    pub fn new() -> Self {
+        Self { operations: Default::default(), interval_start: AtomicU64::new(0) }
+    }
+
+    /// Account a completed operation
+    pub fn record(&self, operation: Operation, bytes: u64, latency_ns: u64) {
+        let stats = &self.operations[operation.index()];
+        stats.latency.record(latency_ns);
+        stats.operations.fetch_add(1, Ordering::Relaxed);
+        stats.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Account a failed operation; its latency is not part of the histogram
+    pub fn record_error(&self, operation: Operation) {
+        self.operations[operation.index()].errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn latency(&self, operation: Operation) -> HistogramSnapshot {
+        self.operations[operation.index()].latency.snapshot()
+    }
+
+    pub fn report(&self) -> BlockStatsReport {
+        let mut all = HistogramSnapshot::default();
+        let mut reports = [OperationReport::default(); 5];
+        for (stats, report) in self.operations.iter().zip(&mut reports) {
+            let latency = stats.latency.snapshot();
+            all.merge(&latency);
+            *report = OperationReport {
+                operations: stats.operations.load(Ordering::Relaxed),
+                bytes: stats.bytes.load(Ordering::Relaxed),
+                errors: stats.errors.load(Ordering::Relaxed),
+                latency: latency.summary(),
+            };
+        }
+        let [read, write, flush, trim, other] = reports;
+        BlockStatsReport { read, write, flush, trim, other, latency: all.summary() }
+    }
+
+    /// Rates since the previous call, which starts the next interval. The
+    /// first call only starts one and returns zero rates.
+    pub fn throughput(&self, now_ns: u64) -> Throughput {
+        let start = self.interval_start.swap(now_ns, Ordering::Relaxed);
+        let mut deltas = [(0, 0); 5];
+        for (stats, delta) in self.operations.iter().zip(&mut deltas) {
+            let operations = stats.operations.load(Ordering::Relaxed);
+            let bytes = stats.bytes.load(Ordering::Relaxed);
+            let previous_operations = stats.interval_operations.swap(operations, Ordering::Relaxed);
+            let previous_bytes = stats.interval_bytes.swap(bytes, Ordering::Relaxed);
+            *delta = (operations.saturating_sub(previous_operations), bytes.saturating_sub(previous_bytes));
+        }
+        if start == 0 || now_ns <= start {
+            return Throughput::default();
+        }
+
+        let interval_ns = now_ns - start;
+        let per_second = |count: u64| (count as u128 * 1_000_000_000 / interval_ns as u128) as u64;
+        let [read, write, flush, trim, other] = deltas;
+        Throughput {
+            interval_ns,
+            read_iops: per_second(read.0),
+            write_iops: per_second(write.0),
+            other_iops: per_second(flush.0 + trim.0 + other.0),
+            read_bytes_per_sec: per_second(read.1),
+            write_bytes_per_sec: per_second(write.1),
+        }
+    }
+
+    /// Zero every counter and histogram and restart the throughput interval
+    pub fn reset(&self) {
+        for stats in &self.operations {
+            stats.latency.reset();
+            for counter in
+                [&stats.operations, &stats.bytes, &stats.errors, &stats.interval_operations, &stats.interval_bytes]
+            {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+        self.interval_start.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Implemented by every block driver, so monitoring tools query them alike
+pub trait BlockStatsSource {
+    fn block_statistics(&self) -> &BlockStatistics;
+
+    fn block_stats_report(&self) -> BlockStatsReport {
+        self.block_statistics().report()
+    }
+
+    fn block_throughput(&self, now_ns: u64) -> Throughput {
+        self.block_statistics().throughput(now_ns)
+    }
+
+    fn reset_block_stats(&self) {
+        self.block_statistics().reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_per_operation() {
+        let stats = BlockStatistics::new();
+        stats.record(Operation::Read, 4096, 100_000);
+        stats.record(Operation::Read, 4096, 300_000);
+        stats.record(Operation::Write, 512, 50);
+        stats.record(Operation::Flush, 0, 2_000_000);
+        stats.record_error(Operation::Write);
+
+        let report = stats.report();
+        assert_eq!((report.read.operations, report.read.bytes, report.read.latency.mean), (2, 8192, 200_000));
+        assert_eq!((report.write.errors, report.write.latency.p99), (1, 50));
+        assert_eq!((report.total_operations(), report.total_errors()), (4, 1));
+        assert_eq!((report.latency.count, report.latency.min, report.latency.max), (4, 50, 2_000_000));
+        assert_eq!(stats.latency(Operation::Flush).count(), 1);
+
+        stats.reset();
+        assert_eq!(stats.report(), BlockStatsReport::default());
+    }
+
+    #[test]
+    fn test_computes_interval_throughput() {
+        let stats = BlockStatistics::new();
+        stats.record(Operation::Read, 1 << 20, 1000);
+        assert_eq!(stats.throughput(1_000_000_000), Throughput::default());
+
+        // Half a second with two reads, one write and a trim
+        stats.record(Operation::Read, 4096, 1000);
+        stats.record(Operation::Read, 4096, 1000);
+        stats.record(Operation::Write, 1000, 1000);
+        stats.record(Operation::Trim, 0, 1000);
+        let throughput = stats.throughput(1_500_000_000);
+        assert_eq!(
+            throughput,
+            Throughput {
+                interval_ns: 500_000_000,
+                read_iops: 4,
+                write_iops: 2,
+                other_iops: 2,
+                read_bytes_per_sec: 16384,
+                write_bytes_per_sec: 2000,
+            }
+        );
+        assert_eq!(stats.throughput(2_500_000_000).read_iops, 0);
+    }
+}
@@ -0,0 +1,165 @@
+/*
+ * Orion Operating System - Character Device Clients
+ *
+ * Client side of the character device requests, for programs using a
+ * device, and of the devfs requests, for drivers registering theirs and
+ * programs looking them up. The transport is a trait so callers can use
+ * an IPC channel and tests call straight into a CharDevice.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::devfs::{decode_lookup, DevfsRequest, DeviceClass};
+use crate::driver::Readiness;
+use crate::protocol::{read_u32, CharRequest, STATUS_EIO, STATUS_OK};
+
+/// Carries a request to a driver or a server and returns its reply, None
+/// when it did not answer
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Send a request and return the reply payload of a successful call
+fn call<T: Transport>(transport: &mut T, request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = transport.call(request).ok_or(STATUS_EIO)?;
+    match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+pub struct CharClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> CharClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    fn call(&mut self, request: CharRequest) -> Result<Vec<u8>, i32> {
+        call(&mut self.transport, &request.encode())
+    }
+
+    pub fn open(&mut self, flags: u32) -> Result<u32, i32> {
+        read_u32(&self.call(CharRequest::Open { flags })?, 0).ok_or(STATUS_EIO)
+    }
+
+    /// Up to `length` bytes; empty at end of file
+    pub fn read(&mut self, handle: u32, length: u32) -> Result<Vec<u8>, i32> {
+        self.call(CharRequest::Read { handle, length })
+    }
+
+    /// Bytes written, all of `data` unless the handle is non-blocking
+    pub fn write(&mut self, handle: u32, data: &[u8]) -> Result<u32, i32> {
+        read_u32(&self.call(CharRequest::Write { handle, data: data.to_vec() })?, 0).ok_or(STATUS_EIO)
+    }
+
+    pub fn poll(&mut self, handle: u32, interest: Readiness) -> Result<Readiness, i32> {
+        let payload = self.call(CharRequest::Poll { handle, interest: interest.bits() })?;
+        read_u32(&payload, 0).map(Readiness::from_bits).ok_or(STATUS_EIO)
+    }
+
+    pub fn ioctl(&mut self, handle: u32, command: u32, argument: &[u8]) -> Result<Vec<u8>, i32> {
+        self.call(CharRequest::Ioctl { handle, command, argument: argument.to_vec() })
+    }
+
+    pub fn set_flags(&mut self, handle: u32, flags: u32) -> Result<(), i32> {
+        self.call(CharRequest::SetFlags { handle, flags }).map(|_| ())
+    }
+
+    pub fn close(&mut self, handle: u32) -> Result<(), i32> {
+        self.call(CharRequest::Close { handle }).map(|_| ())
+    }
+}
+
+pub struct DevfsClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> DevfsClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Create /dev/<name>, served on `endpoint`
+    pub fn register(&mut self, name: &str, class: DeviceClass, endpoint: &str) -> Result<(), i32> {
+        let request = DevfsRequest::Register { name: name.into(), class, endpoint: endpoint.into() };
+        call(&mut self.transport, &request.encode()).map(|_| ())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Result<(), i32> {
+        call(&mut self.transport, &DevfsRequest::Unregister { name: name.into() }.encode()).map(|_| ())
+    }
+
+    /// Class of /dev/<name> and the endpoint serving it
+    pub fn lookup(&mut self, name: &str) -> Result<(DeviceClass, String), i32> {
+        let payload = call(&mut self.transport, &DevfsRequest::Lookup { name: name.into() }.encode())?;
+        decode_lookup(&payload).ok_or(STATUS_EIO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::CharDevice;
+    use crate::driver::{CharDriver, CharError};
+    use crate::protocol::OPEN_NONBLOCK;
+
+    /// Echoes back what was written
+    struct Echo(Vec<u8>);
+
+    impl CharDriver for Echo {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, CharError> {
+            if self.0.is_empty() {
+                return Err(CharError::WouldBlock);
+            }
+            let count = buffer.len().min(self.0.len());
+            buffer[..count].copy_from_slice(&self.0[..count]);
+            self.0.drain(..count);
+            Ok(count)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, CharError> {
+            self.0.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn poll(&mut self) -> Readiness {
+            if self.0.is_empty() {
+                Readiness::WRITABLE
+            } else {
+                Readiness::READABLE | Readiness::WRITABLE
+            }
+        }
+    }
+
+    struct Direct<'a>(&'a mut CharDevice<Echo>);
+
+    impl Transport for Direct<'_> {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            self.0.handle(7, request).pop().map(|(_, response)| response)
+        }
+    }
+
+    #[test]
+    fn test_uses_a_device_through_requests() {
+        let mut device = CharDevice::new(Echo(Vec::new()));
+        let mut client = CharClient::new(Direct(&mut device));
+        let handle = client.open(OPEN_NONBLOCK).unwrap();
+        assert_eq!(client.read(handle, 16), Err(CharError::WouldBlock.status()));
+        assert_eq!(client.poll(handle, Readiness::READABLE), Ok(Readiness::NONE));
+        assert_eq!(client.write(handle, b"ping"), Ok(4));
+        assert_eq!(client.poll(handle, Readiness::READABLE), Ok(Readiness::READABLE));
+        assert_eq!(client.read(handle, 16).unwrap(), b"ping");
+        client.close(handle).unwrap();
+        assert_eq!(client.read(handle, 16), Err(CharError::BadHandle.status()));
+    }
+}
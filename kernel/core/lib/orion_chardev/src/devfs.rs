@@ -0,0 +1,173 @@
+/*
+ * Orion Operating System - Device File System Requests
+ *
+ * Requests to the file system server about the character devices in
+ * /dev. Encoded as the character device requests are (see protocol.rs);
+ * strings are a `len: u32` followed by UTF-8 bytes.
+ *
+ *   DEVFS_REGISTER   name class:u32 endpoint -> (empty)
+ *   DEVFS_UNREGISTER name                    -> (empty)
+ *   DEVFS_LOOKUP     name                    -> class:u32 endpoint
+ *
+ * A driver registers each device it serves under a name, which becomes
+ * /dev/<name>, with the IPC endpoint that serves it; LOOKUP tells a
+ * program which endpoint to send its requests to. A name is registered
+ * once: EEXIST for any other process than the one that registered it,
+ * which may register it again to move it to another endpoint.
+ * Registering and unregistering need CAP_ADMIN, the io service
+ * unregistering the devices of a driver it stopped; looking up needs
+ * CAP_READ.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::protocol::read_u32;
+
+// Opcodes, in the range of the file system server's own requests
+pub const DEVFS_REGISTER: u32 = 0x4A;
+pub const DEVFS_UNREGISTER: u32 = 0x4B;
+pub const DEVFS_LOOKUP: u32 = 0x4C;
+
+/// Longest device name, and longest endpoint name
+pub const DEVICE_NAME_MAX: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceClass {
+    Serial = 1,
+    Tty = 2,
+    Rng = 3,
+    Misc = 4,
+}
+
+impl DeviceClass {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            1 => Some(DeviceClass::Serial),
+            2 => Some(DeviceClass::Tty),
+            3 => Some(DeviceClass::Rng),
+            4 => Some(DeviceClass::Misc),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `name` can name a device or an endpoint: letters, digits,
+/// '-' and '_', nothing that could climb out of /dev
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= DEVICE_NAME_MAX
+        && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + len))
+}
+
+pub(crate) fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DevfsRequest {
+    Register { name: String, class: DeviceClass, endpoint: String },
+    Unregister { name: String },
+    Lookup { name: String },
+}
+
+impl DevfsRequest {
+    /// None for anything else, and for names not valid_name
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let (name, next) = read_string(data, 4)?;
+        if !valid_name(&name) {
+            return None;
+        }
+        match read_u32(data, 0)? {
+            DEVFS_REGISTER => {
+                let class = DeviceClass::from_u32(read_u32(data, next)?)?;
+                let (endpoint, _) = read_string(data, next + 4)?;
+                valid_name(&endpoint).then_some(DevfsRequest::Register { name, class, endpoint })
+            }
+            DEVFS_UNREGISTER => Some(DevfsRequest::Unregister { name }),
+            DEVFS_LOOKUP => Some(DevfsRequest::Lookup { name }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            DevfsRequest::Register { name, class, endpoint } => {
+                out.extend_from_slice(&DEVFS_REGISTER.to_le_bytes());
+                put_string(&mut out, name);
+                out.extend_from_slice(&(*class as u32).to_le_bytes());
+                put_string(&mut out, endpoint);
+            }
+            DevfsRequest::Unregister { name } => {
+                out.extend_from_slice(&DEVFS_UNREGISTER.to_le_bytes());
+                put_string(&mut out, name);
+            }
+            DevfsRequest::Lookup { name } => {
+                out.extend_from_slice(&DEVFS_LOOKUP.to_le_bytes());
+                put_string(&mut out, name);
+            }
+        }
+        out
+    }
+
+    /// The /dev entry the request is about
+    pub fn path(&self) -> String {
+        let name = match self {
+            DevfsRequest::Register { name, .. } | DevfsRequest::Unregister { name } | DevfsRequest::Lookup { name } => {
+                name
+            }
+        };
+        let mut path = String::from("/dev/");
+        path.push_str(name);
+        path
+    }
+}
+
+/// LOOKUP reply payload
+pub fn encode_lookup(class: DeviceClass, endpoint: &str) -> Vec<u8> {
+    let mut out = (class as u32).to_le_bytes().to_vec();
+    put_string(&mut out, endpoint);
+    out
+}
+
+pub fn decode_lookup(payload: &[u8]) -> Option<(DeviceClass, String)> {
+    Some((DeviceClass::from_u32(read_u32(payload, 0)?)?, read_string(payload, 4)?.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_round_trip() {
+        let register =
+            DevfsRequest::Register { name: "ttyS0".into(), class: DeviceClass::Serial, endpoint: "serial".into() };
+        assert_eq!(DevfsRequest::decode(&register.encode()), Some(register.clone()));
+        assert_eq!(register.path(), "/dev/ttyS0");
+        let lookup = DevfsRequest::Lookup { name: "hwrng".into() };
+        assert_eq!(DevfsRequest::decode(&lookup.encode()), Some(lookup));
+
+        // Nothing outside /dev
+        for name in ["", "../etc", "tty/0", "a-name-much-longer-than-thirty-two-bytes"] {
+            assert_eq!(DevfsRequest::decode(&DevfsRequest::Unregister { name: name.into() }.encode()), None);
+        }
+        assert_eq!(
+            decode_lookup(&encode_lookup(DeviceClass::Rng, "virtio-rng")),
+            Some((DeviceClass::Rng, "virtio-rng".into()))
+        );
+    }
+}
@@ -0,0 +1,389 @@
+/*
+ * Orion Operating System - Character Device
+ *
+ * Serves a CharDriver on the driver's endpoint: keeps the handles opened
+ * on the device and makes the requests of blocking handles wait. A
+ * request the driver cannot complete yet is parked, and every time the
+ * device may have become ready (an interrupt, or a poll of the hardware)
+ * the driver loop calls wake, which retries the parked requests in the
+ * order they came and returns the replies of those now done.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_proto::reply;
+
+use crate::driver::{CharDriver, CharError, Readiness};
+use crate::protocol::{CharRequest, OPEN_NONBLOCK, STATUS_EINVAL, STATUS_OK};
+
+/// Most bytes one READ returns
+pub const MAX_TRANSFER: usize = 4096;
+
+/// Handles open on a device at once
+pub const MAX_HANDLES: usize = 64;
+
+/// Replies to send, and to whom
+pub type Replies = Vec<(u64, Vec<u8>)>;
+
+struct Handle {
+    owner: u64,
+    flags: u32,
+}
+
+/// A request left to complete
+enum Wait {
+    Read { length: usize },
+    Write { data: Vec<u8>, written: usize },
+    Poll { interest: Readiness },
+}
+
+struct Waiter {
+    sender: u64,
+    handle: u32,
+    wait: Wait,
+}
+
+/// Try to complete a request; its reply once done
+fn attempt<D: CharDriver>(driver: &mut D, wait: &mut Wait) -> Option<Vec<u8>> {
+    match wait {
+        Wait::Read { length } => {
+            let mut buffer = vec![0; *length];
+            match driver.read(&mut buffer) {
+                Ok(count) => {
+                    buffer.truncate(count);
+                    Some(reply(STATUS_OK, &buffer))
+                }
+                Err(CharError::WouldBlock) => None,
+                Err(error) => Some(reply(error.status(), &[])),
+            }
+        }
+        Wait::Write { data, written } => {
+            while *written < data.len() {
+                match driver.write(&data[*written..]) {
+                    Ok(0) | Err(CharError::WouldBlock) => return None,
+                    Ok(count) => *written += count.min(data.len() - *written),
+                    // What went out before the error is reported
+                    Err(_) if *written > 0 => break,
+                    Err(error) => return Some(reply(error.status(), &[])),
+                }
+            }
+            Some(reply(STATUS_OK, &(*written as u32).to_le_bytes()))
+        }
+        Wait::Poll { interest } => {
+            let ready = driver.poll() & (*interest | Readiness::HANGUP | Readiness::ERROR);
+            (!ready.is_empty()).then(|| reply(STATUS_OK, &ready.bits().to_le_bytes()))
+        }
+    }
+}
+
+pub struct CharDevice<D: CharDriver> {
+    driver: D,
+    handles: BTreeMap<u32, Handle>,
+    next_handle: u32,
+    /// Parked requests, oldest first
+    waiters: VecDeque<Waiter>,
+}
+
+impl<D: CharDriver> CharDevice<D> {
+    pub fn new(driver: D) -> Self {
+        Self { driver, handles: BTreeMap::new(), next_handle: 1, waiters: VecDeque::new() }
+    }
+
+    pub fn driver(&self) -> &D {
+        &self.driver
+    }
+
+    pub fn driver_mut(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    pub fn open_handles(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Whether requests are parked, for the driver loop to keep checking
+    /// the device rather than sleep until the next message
+    pub fn has_waiters(&self) -> bool {
+        !self.waiters.is_empty()
+    }
+
+    /// Serve a request from `sender`. The replies include the sender's,
+    /// unless its request was parked, and those of the requests a CLOSE
+    /// cut short
+    pub fn handle(&mut self, sender: u64, request: &[u8]) -> Replies {
+        let mut replies = Vec::new();
+        let response = match CharRequest::decode(request) {
+            Some(request) => match self.serve(sender, request, &mut replies) {
+                Ok(Some(response)) => response,
+                Ok(None) => return replies,
+                Err(error) => reply(error.status(), &[]),
+            },
+            None => reply(STATUS_EINVAL, &[]),
+        };
+        replies.push((sender, response));
+        replies
+    }
+
+    /// Retry the parked requests, the device may be ready for them; the
+    /// replies of those now done
+    pub fn wake(&mut self) -> Replies {
+        let mut replies = Vec::new();
+        // Once a read or a write has to wait, later ones of its kind wait
+        // behind it
+        let (mut read_blocked, mut write_blocked) = (false, false);
+        let mut index = 0;
+        while index < self.waiters.len() {
+            let waiter = &mut self.waiters[index];
+            let blocked = match waiter.wait {
+                Wait::Read { .. } => &mut read_blocked,
+                Wait::Write { .. } => &mut write_blocked,
+                Wait::Poll { .. } => &mut false,
+            };
+            let done = if *blocked { None } else { attempt(&mut self.driver, &mut waiter.wait) };
+            match done {
+                Some(response) => {
+                    replies.push((waiter.sender, response));
+                    self.waiters.remove(index);
+                }
+                None => {
+                    *blocked = true;
+                    index += 1;
+                }
+            }
+        }
+        replies
+    }
+
+    /// The reply to send now, None when the request was parked
+    fn serve(
+        &mut self,
+        sender: u64,
+        request: CharRequest,
+        replies: &mut Replies,
+    ) -> Result<Option<Vec<u8>>, CharError> {
+        let handle = match request.handle() {
+            Some(handle) => handle,
+            None => return self.open(sender, request).map(Some),
+        };
+        let flags = match self.handles.get(&handle) {
+            Some(open) if open.owner == sender => open.flags,
+            _ => return Err(CharError::BadHandle),
+        };
+        let nonblocking = flags & OPEN_NONBLOCK != 0;
+        match request {
+            CharRequest::Read { length, .. } => match (length as usize).min(MAX_TRANSFER) {
+                0 => Ok(Some(reply(STATUS_OK, &[]))),
+                length => self.start(sender, handle, nonblocking, Wait::Read { length }),
+            },
+            CharRequest::Write { data, .. } => {
+                self.start(sender, handle, nonblocking, Wait::Write { data, written: 0 })
+            }
+            CharRequest::Poll { interest, .. } => {
+                self.start(sender, handle, nonblocking, Wait::Poll { interest: Readiness::from_bits(interest) })
+            }
+            CharRequest::Ioctl { command, argument, .. } => {
+                self.driver.ioctl(command, &argument).map(|payload| Some(reply(STATUS_OK, &payload)))
+            }
+            CharRequest::SetFlags { flags, .. } => {
+                if let Some(open) = self.handles.get_mut(&handle) {
+                    open.flags = flags;
+                }
+                Ok(Some(reply(STATUS_OK, &[])))
+            }
+            CharRequest::Close { .. } => {
+                self.handles.remove(&handle);
+                let interrupted = reply(CharError::Interrupted.status(), &[]);
+                self.waiters.retain(|waiter| {
+                    if waiter.handle != handle {
+                        return true;
+                    }
+                    replies.push((waiter.sender, interrupted.clone()));
+                    false
+                });
+                self.driver.release(self.handles.len());
+                Ok(Some(reply(STATUS_OK, &[])))
+            }
+            CharRequest::Open { .. } => Err(CharError::InvalidArgument),
+        }
+    }
+
+    fn open(&mut self, sender: u64, request: CharRequest) -> Result<Vec<u8>, CharError> {
+        let CharRequest::Open { flags } = request else {
+            return Err(CharError::InvalidArgument);
+        };
+        if self.handles.len() >= MAX_HANDLES {
+            return Err(CharError::TooManyHandles);
+        }
+        self.driver.open(self.handles.len())?;
+        while self.next_handle == 0 || self.handles.contains_key(&self.next_handle) {
+            self.next_handle = self.next_handle.wrapping_add(1);
+        }
+        let handle = self.next_handle;
+        self.next_handle = handle.wrapping_add(1);
+        self.handles.insert(handle, Handle { owner: sender, flags });
+        Ok(reply(STATUS_OK, &handle.to_le_bytes()))
+    }
+
+    /// Complete a request at once or, on a blocking handle, park it
+    fn start(
+        &mut self,
+        sender: u64,
+        handle: u32,
+        nonblocking: bool,
+        mut wait: Wait,
+    ) -> Result<Option<Vec<u8>>, CharError> {
+        let queued = match wait {
+            Wait::Read { .. } => self.waiters.iter().any(|waiter| matches!(waiter.wait, Wait::Read { .. })),
+            Wait::Write { .. } => self.waiters.iter().any(|waiter| matches!(waiter.wait, Wait::Write { .. })),
+            Wait::Poll { .. } => false,
+        };
+        if !queued {
+            if let Some(response) = attempt(&mut self.driver, &mut wait) {
+                return Ok(Some(response));
+            }
+        }
+        if nonblocking {
+            return match wait {
+                Wait::Write { written, .. } if written > 0 => {
+                    Ok(Some(reply(STATUS_OK, &(written as u32).to_le_bytes())))
+                }
+                Wait::Poll { .. } => Ok(Some(reply(STATUS_OK, &0u32.to_le_bytes()))),
+                _ => Err(CharError::WouldBlock),
+            };
+        }
+        self.waiters.push_back(Waiter { sender, handle, wait });
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::read_u32;
+
+    /// A device receiving what `incoming` holds and sending up to `room`
+    /// bytes before its transmitter is full
+    struct Loopback {
+        incoming: VecDeque<u8>,
+        sent: Vec<u8>,
+        room: usize,
+    }
+
+    impl Loopback {
+        fn new(room: usize) -> Self {
+            Self { incoming: VecDeque::new(), sent: Vec::new(), room }
+        }
+    }
+
+    impl CharDriver for Loopback {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, CharError> {
+            if self.incoming.is_empty() {
+                return Err(CharError::WouldBlock);
+            }
+            let count = buffer.len().min(self.incoming.len());
+            for (slot, byte) in buffer.iter_mut().zip(self.incoming.drain(..count)) {
+                *slot = byte;
+            }
+            Ok(count)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, CharError> {
+            let count = data.len().min(self.room);
+            if count == 0 {
+                return Err(CharError::WouldBlock);
+            }
+            self.sent.extend_from_slice(&data[..count]);
+            self.room -= count;
+            Ok(count)
+        }
+
+        fn poll(&mut self) -> Readiness {
+            let mut ready = Readiness::NONE;
+            if !self.incoming.is_empty() {
+                ready = ready | Readiness::READABLE;
+            }
+            if self.room > 0 {
+                ready = ready | Readiness::WRITABLE;
+            }
+            ready
+        }
+    }
+
+    fn request(request: CharRequest) -> Vec<u8> {
+        request.encode()
+    }
+
+    fn status(response: &[u8]) -> i32 {
+        read_u32(response, 0).unwrap() as i32
+    }
+
+    fn open(device: &mut CharDevice<Loopback>, sender: u64, flags: u32) -> u32 {
+        let replies = device.handle(sender, &request(CharRequest::Open { flags }));
+        read_u32(&replies[0].1, 4).unwrap()
+    }
+
+    #[test]
+    fn test_blocking_requests_wait_in_order() {
+        let mut device = CharDevice::new(Loopback::new(4));
+        let first = open(&mut device, 10, 0);
+        let second = open(&mut device, 20, 0);
+
+        // Both reads wait, and are answered in the order they came; the
+        // poll sees what they leave
+        assert!(device.handle(10, &request(CharRequest::Read { handle: first, length: 2 })).is_empty());
+        assert!(device.handle(20, &request(CharRequest::Read { handle: second, length: 1 })).is_empty());
+        assert!(device.handle(20, &request(CharRequest::Poll { handle: second, interest: 1 })).is_empty());
+        assert!(device.has_waiters());
+        device.driver_mut().incoming.extend(b"abcd");
+        let replies = device.wake();
+        assert_eq!(replies.len(), 3);
+        assert_eq!((replies[0].0, &replies[0].1[4..]), (10, &b"ab"[..]));
+        assert_eq!((replies[1].0, &replies[1].1[4..]), (20, &b"c"[..]));
+        assert_eq!((replies[2].0, read_u32(&replies[2].1, 4)), (20, Some(Readiness::READABLE.bits())));
+
+        // A write waits until all of it is taken
+        assert!(device.handle(10, &request(CharRequest::Write { handle: first, data: b"hello".to_vec() })).is_empty());
+        assert_eq!(device.driver().sent, b"hell");
+        assert!(device.wake().is_empty());
+        device.driver_mut().room = 8;
+        let replies = device.wake();
+        assert_eq!((replies[0].0, read_u32(&replies[0].1, 4)), (10, Some(5)));
+        assert!(!device.has_waiters());
+    }
+
+    #[test]
+    fn test_nonblocking_handles_and_close() {
+        let mut device = CharDevice::new(Loopback::new(2));
+        let handle = open(&mut device, 10, OPEN_NONBLOCK);
+
+        let replies = device.handle(10, &request(CharRequest::Read { handle, length: 16 }));
+        assert_eq!(status(&replies[0].1), CharError::WouldBlock.status());
+        let replies = device.handle(10, &request(CharRequest::Write { handle, data: b"xyz".to_vec() }));
+        assert_eq!(read_u32(&replies[0].1, 4), Some(2));
+        let replies = device.handle(10, &request(CharRequest::Poll { handle, interest: 3 }));
+        assert_eq!(read_u32(&replies[0].1, 4), Some(0));
+
+        // Only the owner uses a handle
+        let replies = device.handle(11, &request(CharRequest::Read { handle, length: 16 }));
+        assert_eq!(status(&replies[0].1), CharError::BadHandle.status());
+        let replies = device.handle(10, &request(CharRequest::Ioctl { handle, command: 1, argument: Vec::new() }));
+        assert_eq!(status(&replies[0].1), CharError::NotSupported.status());
+
+        // Closing answers what waits on the handle
+        device.handle(10, &request(CharRequest::SetFlags { handle, flags: 0 }));
+        assert!(device.handle(10, &request(CharRequest::Read { handle, length: 16 })).is_empty());
+        let replies = device.handle(10, &request(CharRequest::Close { handle }));
+        assert_eq!(replies.len(), 2);
+        assert_eq!(status(&replies[0].1), CharError::Interrupted.status());
+        assert_eq!(status(&replies[1].1), STATUS_OK);
+        assert_eq!((device.open_handles(), device.has_waiters()), (0, false));
+    }
+}
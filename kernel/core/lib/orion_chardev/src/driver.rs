@@ -0,0 +1,122 @@
+/*
+ * Orion Operating System - Character Driver
+ *
+ * What a character device driver implements. Every call returns at once:
+ * a read with nothing to read and a write the device has no room for
+ * fail with WouldBlock, and poll says which of the two would now do
+ * something. Waiting is left to the CharDevice serving the driver, which
+ * retries the calls once the device may be ready again.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+use core::ops::{BitAnd, BitOr};
+
+/// Readiness of a device, as poll reports it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Readiness(u32);
+
+impl Readiness {
+    pub const NONE: Readiness = Readiness(0);
+    /// A read returns data, or end of file after a hangup
+    pub const READABLE: Readiness = Readiness(1 << 0);
+    /// A write accepts at least one byte
+    pub const WRITABLE: Readiness = Readiness(1 << 1);
+    /// The other end went away: the line dropped or the device was removed
+    pub const HANGUP: Readiness = Readiness(1 << 2);
+    pub const ERROR: Readiness = Readiness(1 << 3);
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Readiness(bits & 0xF)
+    }
+
+    pub const fn contains(self, other: Readiness) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Readiness {
+    type Output = Readiness;
+
+    fn bitor(self, other: Readiness) -> Readiness {
+        Readiness(self.0 | other.0)
+    }
+}
+
+impl BitAnd for Readiness {
+    type Output = Readiness;
+
+    fn bitand(self, other: Readiness) -> Readiness {
+        Readiness(self.0 & other.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharError {
+    /// Nothing to read or no room to write now
+    WouldBlock,
+    InvalidArgument,
+    /// An ioctl the device does not have
+    NotSupported,
+    IoError,
+    Busy,
+    /// A handle not open, or not opened by the caller
+    BadHandle,
+    /// A blocked request cut short by the close of its handle
+    Interrupted,
+    TooManyHandles,
+}
+
+impl CharError {
+    /// Reply status, a negative errno
+    pub fn status(self) -> i32 {
+        match self {
+            CharError::WouldBlock => -11,
+            CharError::InvalidArgument => -22,
+            CharError::NotSupported => -25,
+            CharError::IoError => -5,
+            CharError::Busy => -16,
+            CharError::BadHandle => -9,
+            CharError::Interrupted => -4,
+            CharError::TooManyHandles => -24,
+        }
+    }
+}
+
+pub trait CharDriver {
+    /// Move up to `buffer.len()` bytes into `buffer`; Ok(0) is end of
+    /// file, for a device that hung up
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, CharError>;
+
+    /// Accept as much of `data` as the device has room for, at least one
+    /// byte
+    fn write(&mut self, data: &[u8]) -> Result<usize, CharError>;
+
+    fn poll(&mut self) -> Readiness;
+
+    /// Device specific requests; the reply payload on success
+    fn ioctl(&mut self, _command: u32, _argument: &[u8]) -> Result<Vec<u8>, CharError> {
+        Err(CharError::NotSupported)
+    }
+
+    /// A handle is being opened; Busy refuses it, for exclusive devices
+    fn open(&mut self, _open_handles: usize) -> Result<(), CharError> {
+        Ok(())
+    }
+
+    /// A handle was closed, `open_handles` remain
+    fn release(&mut self, _open_handles: usize) {}
+}
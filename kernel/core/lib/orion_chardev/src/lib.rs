@@ -0,0 +1,38 @@
+/*
+ * Orion Operating System - Character Devices
+ *
+ * Framework for the drivers of byte-stream devices: serial ports, ttys,
+ * random number generators and the odd misc device. orion_driver has
+ * NetworkDriver and BlockDriver for the devices behind the net and block
+ * servers; a character device has no server in front of it, so its
+ * driver implements CharDriver and serves its own endpoint through a
+ * CharDevice, which keeps the open handles and turns the driver's
+ * non-blocking calls into blocking ones for the handles that want them
+ * (see device.rs and protocol.rs).
+ *
+ * The driver then registers its device with devfs, the /dev directory
+ * of the file system server (see devfs.rs), where programs look up the
+ * endpoint serving a name such as "ttyS0".
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod client;
+pub mod devfs;
+pub mod device;
+pub mod driver;
+pub mod protocol;
+
+pub use client::{CharClient, DevfsClient, Transport};
+pub use devfs::{DevfsRequest, DeviceClass, DEVICE_NAME_MAX};
+pub use device::{CharDevice, Replies, MAX_HANDLES, MAX_TRANSFER};
+pub use driver::{CharDriver, CharError, Readiness};
+pub use protocol::{CharRequest, OPEN_NONBLOCK};
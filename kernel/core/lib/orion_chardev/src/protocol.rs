@@ -0,0 +1,166 @@
+/*
+ * Orion Operating System - Character Device Requests
+ *
+ * Requests a character device driver accepts on its endpoint. All fields
+ * are little-endian; every request starts with a 32-bit opcode and every
+ * reply with a 32-bit signed status (0 or a negative errno).
+ *
+ *   OPEN      flags:u32                    -> handle:u32
+ *   READ      handle:u32 length:u32        -> data
+ *   WRITE     handle:u32 data              -> written:u32
+ *   POLL      handle:u32 interest:u32      -> ready:u32
+ *   IOCTL     handle:u32 command:u32 data  -> (device specific)
+ *   SET_FLAGS handle:u32 flags:u32         -> (empty)
+ *   CLOSE     handle:u32                   -> (empty)
+ *
+ * A handle belongs to the process that opened it. Unless it was opened
+ * with OPEN_NONBLOCK, READ waits for at least one byte and returns what
+ * there is up to `length`, WRITE waits until all of its data is taken
+ * and POLL until one of the `interest` readiness bits, or HANGUP or
+ * ERROR, is set; requests of one kind are answered in the order they
+ * came. A non-blocking handle gets EAGAIN instead of waiting, a partial
+ * count from WRITE, and the current readiness from POLL. Closing a
+ * handle answers its waiting requests with EINTR.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+// Opcodes
+pub const CHAR_OPEN: u32 = 0x6001;
+pub const CHAR_READ: u32 = 0x6002;
+pub const CHAR_WRITE: u32 = 0x6003;
+pub const CHAR_POLL: u32 = 0x6004;
+pub const CHAR_IOCTL: u32 = 0x6005;
+pub const CHAR_SET_FLAGS: u32 = 0x6006;
+pub const CHAR_CLOSE: u32 = 0x6007;
+
+/// Handle flag: fail with EAGAIN rather than wait
+pub const OPEN_NONBLOCK: u32 = 1 << 0;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EINVAL: i32 = -22;
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharRequest {
+    Open { flags: u32 },
+    Read { handle: u32, length: u32 },
+    Write { handle: u32, data: Vec<u8> },
+    Poll { handle: u32, interest: u32 },
+    Ioctl { handle: u32, command: u32, argument: Vec<u8> },
+    SetFlags { handle: u32, flags: u32 },
+    Close { handle: u32 },
+}
+
+impl CharRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let handle = || read_u32(data, 4);
+        Some(match read_u32(data, 0)? {
+            CHAR_OPEN => CharRequest::Open { flags: read_u32(data, 4)? },
+            CHAR_READ => CharRequest::Read { handle: handle()?, length: read_u32(data, 8)? },
+            CHAR_WRITE => CharRequest::Write { handle: handle()?, data: data.get(8..)?.to_vec() },
+            CHAR_POLL => CharRequest::Poll { handle: handle()?, interest: read_u32(data, 8)? },
+            CHAR_IOCTL => CharRequest::Ioctl {
+                handle: handle()?,
+                command: read_u32(data, 8)?,
+                argument: data.get(12..)?.to_vec(),
+            },
+            CHAR_SET_FLAGS => CharRequest::SetFlags { handle: handle()?, flags: read_u32(data, 8)? },
+            CHAR_CLOSE => CharRequest::Close { handle: handle()? },
+            _ => return None,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut put = |value: u32| out.extend_from_slice(&value.to_le_bytes());
+        match self {
+            CharRequest::Open { flags } => {
+                put(CHAR_OPEN);
+                put(*flags);
+            }
+            CharRequest::Read { handle, length } => {
+                put(CHAR_READ);
+                put(*handle);
+                put(*length);
+            }
+            CharRequest::Write { handle, .. } => {
+                put(CHAR_WRITE);
+                put(*handle);
+            }
+            CharRequest::Poll { handle, interest } => {
+                put(CHAR_POLL);
+                put(*handle);
+                put(*interest);
+            }
+            CharRequest::Ioctl { handle, command, .. } => {
+                put(CHAR_IOCTL);
+                put(*handle);
+                put(*command);
+            }
+            CharRequest::SetFlags { handle, flags } => {
+                put(CHAR_SET_FLAGS);
+                put(*handle);
+                put(*flags);
+            }
+            CharRequest::Close { handle } => {
+                put(CHAR_CLOSE);
+                put(*handle);
+            }
+        }
+        match self {
+            CharRequest::Write { data, .. } | CharRequest::Ioctl { argument: data, .. } => out.extend_from_slice(data),
+            _ => {}
+        }
+        out
+    }
+
+    /// Handle the request is about, None for OPEN
+    pub fn handle(&self) -> Option<u32> {
+        match self {
+            CharRequest::Open { .. } => None,
+            CharRequest::Read { handle, .. }
+            | CharRequest::Write { handle, .. }
+            | CharRequest::Poll { handle, .. }
+            | CharRequest::Ioctl { handle, .. }
+            | CharRequest::SetFlags { handle, .. }
+            | CharRequest::Close { handle } => Some(*handle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_requests_round_trip() {
+        for request in [
+            CharRequest::Open { flags: OPEN_NONBLOCK },
+            CharRequest::Read { handle: 3, length: 512 },
+            CharRequest::Write { handle: 3, data: vec![b'o', b'k', b'\n'] },
+            CharRequest::Write { handle: 3, data: vec![] },
+            CharRequest::Poll { handle: 3, interest: 1 },
+            CharRequest::Ioctl { handle: 3, command: 0x5401, argument: vec![1, 2, 3, 4] },
+            CharRequest::SetFlags { handle: 3, flags: 0 },
+            CharRequest::Close { handle: 3 },
+        ] {
+            assert_eq!(CharRequest::decode(&request.encode()), Some(request));
+        }
+        assert_eq!(CharRequest::decode(&CHAR_READ.to_le_bytes()), None);
+        assert_eq!(CharRequest::decode(&0x6100u32.to_le_bytes()), None);
+    }
+}
@@ -0,0 +1,323 @@
+/*
+ * Orion Operating System - AES-256
+ *
+ * AES-256 (FIPS 197) with the two modes file encryption needs: XTS
+ * (IEEE 1619) for contents, where every data unit is encrypted on its own
+ * under a tweak of its number so any unit can be read or rewritten alone,
+ * and CBC for names. The rounds use lookup tables; the fs server is the
+ * only user, and keys it holds never leave it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::wipe;
+
+pub const AES_BLOCK_SIZE: usize = 16;
+pub const AES256_KEY_SIZE: usize = 32;
+/// Data key then tweak key
+pub const XTS_KEY_SIZE: usize = 2 * AES256_KEY_SIZE;
+
+const ROUNDS: usize = 14;
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const INV_SBOX: [u8; 256] = [
+    0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
+    0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
+    0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
+    0x08, 0x2e, 0xa1, 0x66, 0x28, 0xd9, 0x24, 0xb2, 0x76, 0x5b, 0xa2, 0x49, 0x6d, 0x8b, 0xd1, 0x25,
+    0x72, 0xf8, 0xf6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xd4, 0xa4, 0x5c, 0xcc, 0x5d, 0x65, 0xb6, 0x92,
+    0x6c, 0x70, 0x48, 0x50, 0xfd, 0xed, 0xb9, 0xda, 0x5e, 0x15, 0x46, 0x57, 0xa7, 0x8d, 0x9d, 0x84,
+    0x90, 0xd8, 0xab, 0x00, 0x8c, 0xbc, 0xd3, 0x0a, 0xf7, 0xe4, 0x58, 0x05, 0xb8, 0xb3, 0x45, 0x06,
+    0xd0, 0x2c, 0x1e, 0x8f, 0xca, 0x3f, 0x0f, 0x02, 0xc1, 0xaf, 0xbd, 0x03, 0x01, 0x13, 0x8a, 0x6b,
+    0x3a, 0x91, 0x11, 0x41, 0x4f, 0x67, 0xdc, 0xea, 0x97, 0xf2, 0xcf, 0xce, 0xf0, 0xb4, 0xe6, 0x73,
+    0x96, 0xac, 0x74, 0x22, 0xe7, 0xad, 0x35, 0x85, 0xe2, 0xf9, 0x37, 0xe8, 0x1c, 0x75, 0xdf, 0x6e,
+    0x47, 0xf1, 0x1a, 0x71, 0x1d, 0x29, 0xc5, 0x89, 0x6f, 0xb7, 0x62, 0x0e, 0xaa, 0x18, 0xbe, 0x1b,
+    0xfc, 0x56, 0x3e, 0x4b, 0xc6, 0xd2, 0x79, 0x20, 0x9a, 0xdb, 0xc0, 0xfe, 0x78, 0xcd, 0x5a, 0xf4,
+    0x1f, 0xdd, 0xa8, 0x33, 0x88, 0x07, 0xc7, 0x31, 0xb1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xec, 0x5f,
+    0x60, 0x51, 0x7f, 0xa9, 0x19, 0xb5, 0x4a, 0x0d, 0x2d, 0xe5, 0x7a, 0x9f, 0x93, 0xc9, 0x9c, 0xef,
+    0xa0, 0xe0, 0x3b, 0x4d, 0xae, 0x2a, 0xf5, 0xb0, 0xc8, 0xeb, 0xbb, 0x3c, 0x83, 0x53, 0x99, 0x61,
+    0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d,
+];
+
+const RCON: [u8; 7] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40];
+
+type Block = [u8; AES_BLOCK_SIZE];
+
+fn xtime(byte: u8) -> u8 {
+    (byte << 1) ^ if byte & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+fn multiply(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+fn add_round_key(state: &mut Block, key: &Block) {
+    for (byte, key) in state.iter_mut().zip(key) {
+        *byte ^= key;
+    }
+}
+
+/// The state is stored column after column, byte `row + 4 * column`
+fn shift_rows(state: &mut Block, inverse: bool) {
+    let old = *state;
+    for row in 1..4 {
+        for column in 0..4 {
+            let shifted = row + 4 * ((column + row) % 4);
+            if inverse {
+                state[shifted] = old[row + 4 * column];
+            } else {
+                state[row + 4 * column] = old[shifted];
+            }
+        }
+    }
+}
+
+fn mix_columns(state: &mut Block) {
+    for column in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inverse_mix_columns(state: &mut Block) {
+    for column in state.chunks_exact_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for (row, byte) in column.iter_mut().enumerate() {
+            *byte = multiply(a[row], 14)
+                ^ multiply(a[(row + 1) % 4], 11)
+                ^ multiply(a[(row + 2) % 4], 13)
+                ^ multiply(a[(row + 3) % 4], 9);
+        }
+    }
+}
+
+/// An expanded AES-256 key, erased when dropped
+pub struct Aes256 {
+    round_keys: [Block; ROUNDS + 1],
+}
+
+impl Aes256 {
+    pub fn new(key: &[u8; AES256_KEY_SIZE]) -> Self {
+        let mut words = [[0u8; 4]; 4 * (ROUNDS + 1)];
+        for (index, word) in key.chunks_exact(4).enumerate() {
+            words[index].copy_from_slice(word);
+        }
+        for index in 8..words.len() {
+            let mut word = words[index - 1];
+            if index % 8 == 0 {
+                word = [SBOX[word[1] as usize], SBOX[word[2] as usize], SBOX[word[3] as usize], SBOX[word[0] as usize]];
+                word[0] ^= RCON[index / 8 - 1];
+            } else if index % 8 == 4 {
+                word = word.map(|byte| SBOX[byte as usize]);
+            }
+            for (byte, previous) in word.iter_mut().zip(words[index - 8]) {
+                *byte ^= previous;
+            }
+            words[index] = word;
+        }
+
+        let mut round_keys = [[0u8; AES_BLOCK_SIZE]; ROUNDS + 1];
+        for (round, key) in round_keys.iter_mut().enumerate() {
+            for column in 0..4 {
+                key[4 * column..4 * column + 4].copy_from_slice(&words[4 * round + column]);
+            }
+        }
+        wipe(words.as_flattened_mut());
+        Self { round_keys }
+    }
+
+    pub fn encrypt_block(&self, block: &mut Block) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=ROUNDS {
+            for byte in block.iter_mut() {
+                *byte = SBOX[*byte as usize];
+            }
+            shift_rows(block, false);
+            if round != ROUNDS {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    pub fn decrypt_block(&self, block: &mut Block) {
+        add_round_key(block, &self.round_keys[ROUNDS]);
+        for round in (0..ROUNDS).rev() {
+            shift_rows(block, true);
+            for byte in block.iter_mut() {
+                *byte = INV_SBOX[*byte as usize];
+            }
+            add_round_key(block, &self.round_keys[round]);
+            if round != 0 {
+                inverse_mix_columns(block);
+            }
+        }
+    }
+}
+
+impl Drop for Aes256 {
+    fn drop(&mut self) {
+        wipe(self.round_keys.as_flattened_mut());
+    }
+}
+
+/// Multiply a tweak by x in GF(2^128), little-endian as XTS stores it
+fn next_tweak(tweak: &mut Block) {
+    let mut carry = 0;
+    for byte in tweak.iter_mut() {
+        let next = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = next;
+    }
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
+
+/// XTS-AES-256 over data units whose length is a multiple of the block
+pub struct Xts {
+    data: Aes256,
+    tweak: Aes256,
+}
+
+impl Xts {
+    pub fn new(key: &[u8; XTS_KEY_SIZE]) -> Self {
+        let (data, tweak) = key.split_at(AES256_KEY_SIZE);
+        Self { data: Aes256::new(data.try_into().unwrap()), tweak: Aes256::new(tweak.try_into().unwrap()) }
+    }
+
+    /// Encrypt data unit number `unit` in place
+    pub fn encrypt(&self, unit: u64, data: &mut [u8]) {
+        self.process(unit, data, false);
+    }
+
+    pub fn decrypt(&self, unit: u64, data: &mut [u8]) {
+        self.process(unit, data, true);
+    }
+
+    fn process(&self, unit: u64, data: &mut [u8], decrypt: bool) {
+        assert!(data.len().is_multiple_of(AES_BLOCK_SIZE), "XTS units are whole blocks");
+        let mut tweak = [0u8; AES_BLOCK_SIZE];
+        tweak[..8].copy_from_slice(&unit.to_le_bytes());
+        self.tweak.encrypt_block(&mut tweak);
+
+        for chunk in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+            let block: &mut Block = chunk.try_into().unwrap();
+            add_round_key(block, &tweak);
+            if decrypt {
+                self.data.decrypt_block(block);
+            } else {
+                self.data.encrypt_block(block);
+            }
+            add_round_key(block, &tweak);
+            next_tweak(&mut tweak);
+        }
+    }
+}
+
+/// Encrypt `data`, a multiple of the block, in CBC mode
+pub fn cbc_encrypt(cipher: &Aes256, iv: &Block, data: &mut [u8]) {
+    assert!(data.len().is_multiple_of(AES_BLOCK_SIZE), "CBC data is whole blocks");
+    let mut chain = *iv;
+    for chunk in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+        let block: &mut Block = chunk.try_into().unwrap();
+        add_round_key(block, &chain);
+        cipher.encrypt_block(block);
+        chain = *block;
+    }
+}
+
+pub fn cbc_decrypt(cipher: &Aes256, iv: &Block, data: &mut [u8]) {
+    assert!(data.len().is_multiple_of(AES_BLOCK_SIZE), "CBC data is whole blocks");
+    let mut chain = *iv;
+    for chunk in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+        let block: &mut Block = chunk.try_into().unwrap();
+        let ciphertext = *block;
+        cipher.decrypt_block(block);
+        add_round_key(block, &chain);
+        chain = ciphertext;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> [u8; 64] {
+        let mut out = [0u8; 64];
+        for (index, byte) in out.iter_mut().enumerate().take(text.len() / 2) {
+            *byte = u8::from_str_radix(&text[2 * index..2 * index + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_matches_fips_vector() {
+        let key: [u8; 32] = core::array::from_fn(|index| index as u8);
+        let cipher = Aes256::new(&key);
+        let mut block = hex("00112233445566778899aabbccddeeff")[..16].try_into().unwrap();
+        cipher.encrypt_block(&mut block);
+        assert_eq!(block[..], hex("8ea2b7ca516745bfeafc49904b496089")[..16]);
+        cipher.decrypt_block(&mut block);
+        assert_eq!(block[..], hex("00112233445566778899aabbccddeeff")[..16]);
+    }
+
+    #[test]
+    fn test_xts_and_cbc_round_trip() {
+        let key: [u8; 64] = core::array::from_fn(|index| index as u8);
+        let plain: [u8; 64] = core::array::from_fn(|index| (index * 7) as u8);
+        let xts = Xts::new(&key);
+        let mut data = plain;
+        xts.encrypt(5, &mut data);
+        assert_eq!(data[..32], hex("35af686901a5cb6df9c180d99226dfef39241e48cd3d86560a8094e79936ea78")[..32]);
+        assert_eq!(data[32..], hex("26939d2db92bb0900349302ef52b4bf38995074cdd8d4f8ca2a0e8c85d9b4bc1")[..32]);
+        // The unit number is the tweak
+        let mut other = plain;
+        xts.encrypt(6, &mut other);
+        assert_ne!(data, other);
+        xts.decrypt(5, &mut data);
+        assert_eq!(data, plain);
+
+        let cipher = Aes256::new(&[9; 32]);
+        let mut name = [0u8; 32];
+        name[..11].copy_from_slice(b"hello world");
+        cbc_encrypt(&cipher, &[0; 16], &mut name);
+        assert_eq!(name[..], hex("29796b03da6544bab2994d9ffec13f79a03b296face98fce2e2ac73f9651c5d4")[..32]);
+        cbc_decrypt(&cipher, &[0; 16], &mut name);
+        assert_eq!(&name[..12], b"hello world\0");
+    }
+}
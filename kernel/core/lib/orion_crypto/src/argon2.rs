@@ -0,0 +1,305 @@
+/*
+ * Orion Operating System - Argon2id
+ *
+ * Argon2id version 1.3 (RFC 9106), the password hash of the identity
+ * service. The crate does not allocate, so the caller lends the working
+ * memory: one Block per KiB of the memory cost. Lanes are filled one
+ * after the other, which gives the same tags as a parallel fill.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::blake2b::{Blake2b, BLAKE2B_MAX_DIGEST_SIZE};
+
+/// Words in a memory block
+pub const BLOCK_WORDS: usize = 128;
+
+/// One KiB of Argon2 working memory
+pub type Block = [u64; BLOCK_WORDS];
+
+const VERSION: u32 = 0x13;
+const TYPE_ID: u32 = 2;
+const SYNC_POINTS: usize = 4;
+
+/// Cost parameters, stored next to every hash made with them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Params {
+    /// Whether the RFC allows these parameters
+    pub fn is_valid(&self) -> bool {
+        self.iterations >= 1 && (1..(1 << 24)).contains(&self.parallelism) && self.memory_kib >= 8 * self.parallelism
+    }
+
+    /// Blocks of working memory used: the memory cost rounded down to a
+    /// multiple of four blocks per lane
+    pub fn blocks(&self) -> usize {
+        let quarters = SYNC_POINTS * self.parallelism as usize;
+        self.memory_kib as usize / quarters * quarters
+    }
+}
+
+/// Variable-length hash H' over the concatenation of `parts`
+fn hash_long(parts: &[&[u8]], out: &mut [u8]) {
+    let length = (out.len() as u32).to_le_bytes();
+    if out.len() <= BLAKE2B_MAX_DIGEST_SIZE {
+        let mut hash = Blake2b::new(out.len());
+        hash.update(&length);
+        for part in parts {
+            hash.update(part);
+        }
+        hash.finalize(out);
+        return;
+    }
+
+    let mut previous = [0u8; BLAKE2B_MAX_DIGEST_SIZE];
+    let mut hash = Blake2b::new(BLAKE2B_MAX_DIGEST_SIZE);
+    hash.update(&length);
+    for part in parts {
+        hash.update(part);
+    }
+    hash.finalize(&mut previous);
+    out[..32].copy_from_slice(&previous[..32]);
+
+    // Each further hash contributes its first half, the last one all of it
+    let mut written = 32;
+    while out.len() - written > BLAKE2B_MAX_DIGEST_SIZE {
+        let input = previous;
+        Blake2b::digest(&input, &mut previous);
+        out[written..written + 32].copy_from_slice(&previous[..32]);
+        written += 32;
+    }
+    let input = previous;
+    Blake2b::digest(&input, &mut out[written..]);
+}
+
+fn mix(v: &mut Block, a: usize, b: usize, c: usize, d: usize) {
+    let multiply = |x: u64, y: u64| 2u64.wrapping_mul(x & 0xffff_ffff).wrapping_mul(y & 0xffff_ffff);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(multiply(v[a], v[b]));
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]).wrapping_add(multiply(v[c], v[d]));
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(multiply(v[a], v[b]));
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]).wrapping_add(multiply(v[c], v[d]));
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// The BLAKE2b round of the compression function, over the 16 words at `index`
+fn permute(v: &mut Block, index: [usize; 16]) {
+    let [i0, i1, i2, i3, i4, i5, i6, i7, i8, i9, i10, i11, i12, i13, i14, i15] = index;
+    mix(v, i0, i4, i8, i12);
+    mix(v, i1, i5, i9, i13);
+    mix(v, i2, i6, i10, i14);
+    mix(v, i3, i7, i11, i15);
+    mix(v, i0, i5, i10, i15);
+    mix(v, i1, i6, i11, i12);
+    mix(v, i2, i7, i8, i13);
+    mix(v, i3, i4, i9, i14);
+}
+
+/// Compression function G; with `accumulate` the result is XORed into `out`
+/// instead of replacing it, as passes after the first do
+fn compress(x: &Block, y: &Block, out: &mut Block, accumulate: bool) {
+    let mut r = [0u64; BLOCK_WORDS];
+    for (word, (a, b)) in r.iter_mut().zip(x.iter().zip(y.iter())) {
+        *word = a ^ b;
+    }
+    let mut q = r;
+    for row in 0..8 {
+        permute(&mut q, core::array::from_fn(|word| 16 * row + word));
+    }
+    for column in 0..8 {
+        permute(&mut q, core::array::from_fn(|word| 2 * column + 16 * (word / 2) + word % 2));
+    }
+    for (index, word) in out.iter_mut().enumerate() {
+        let value = q[index] ^ r[index];
+        *word = if accumulate { *word ^ value } else { value };
+    }
+}
+
+struct Position {
+    pass: usize,
+    lane: usize,
+    slice: usize,
+    index: usize,
+}
+
+/// Index within the reference lane of the block mixed in at `position`
+fn reference_index(position: &Position, segment: usize, random: u64, same_lane: bool) -> usize {
+    let lane_length = segment * SYNC_POINTS;
+    let finished = if position.pass == 0 { position.slice * segment } else { lane_length - segment };
+    let area = if same_lane {
+        finished + position.index - 1
+    } else if position.index == 0 {
+        finished - 1
+    } else {
+        finished
+    } as u64;
+
+    let random = random & 0xffff_ffff;
+    let relative = area - 1 - ((area * ((random * random) >> 32)) >> 32);
+    let start =
+        if position.pass == 0 || position.slice == SYNC_POINTS - 1 { 0 } else { (position.slice + 1) * segment };
+    (start + relative as usize) % lane_length
+}
+
+/// Next block of pseudo-random addresses for data-independent indexing
+fn next_addresses(input: &mut Block, addresses: &mut Block) {
+    let zero = [0u64; BLOCK_WORDS];
+    input[6] += 1;
+    compress(&zero, input, addresses, false);
+    let first = *addresses;
+    compress(&zero, &first, addresses, false);
+}
+
+fn fill_segment(memory: &mut [Block], params: &Params, position: &mut Position) {
+    let lanes = params.parallelism as usize;
+    let lane_length = memory.len() / lanes;
+    let segment = lane_length / SYNC_POINTS;
+    // Argon2id indexes like Argon2i for the first half of the first pass
+    let independent = position.pass == 0 && position.slice < SYNC_POINTS / 2;
+
+    let mut input = [0u64; BLOCK_WORDS];
+    let mut addresses = [0u64; BLOCK_WORDS];
+    if independent {
+        input[..6].copy_from_slice(&[
+            position.pass as u64,
+            position.lane as u64,
+            position.slice as u64,
+            memory.len() as u64,
+            params.iterations as u64,
+            TYPE_ID as u64,
+        ]);
+    }
+
+    let first = if position.pass == 0 && position.slice == 0 { 2 } else { 0 };
+    if independent && first != 0 {
+        next_addresses(&mut input, &mut addresses);
+    }
+
+    for index in first..segment {
+        let offset = position.lane * lane_length + position.slice * segment + index;
+        let previous = if offset.is_multiple_of(lane_length) { offset + lane_length - 1 } else { offset - 1 };
+
+        let random = if independent {
+            if index.is_multiple_of(BLOCK_WORDS) {
+                next_addresses(&mut input, &mut addresses);
+            }
+            addresses[index % BLOCK_WORDS]
+        } else {
+            memory[previous][0]
+        };
+
+        let reference_lane =
+            if position.pass == 0 && position.slice == 0 { position.lane } else { (random >> 32) as usize % lanes };
+        position.index = index;
+        let reference =
+            reference_lane * lane_length + reference_index(position, segment, random, reference_lane == position.lane);
+
+        let (previous_block, reference_block) = (memory[previous], memory[reference]);
+        compress(&previous_block, &reference_block, &mut memory[offset], position.pass > 0);
+    }
+}
+
+/// Hash `password` with Argon2id into `out`. `secret` and `associated`
+/// may be empty. `memory` must hold at least `params.blocks()` blocks.
+///
+/// Panics on invalid parameters, short memory or an output shorter than
+/// 4 bytes
+pub fn argon2id(
+    password: &[u8],
+    salt: &[u8],
+    secret: &[u8],
+    associated: &[u8],
+    params: &Params,
+    memory: &mut [Block],
+    out: &mut [u8],
+) {
+    assert!(params.is_valid(), "invalid Argon2 parameters");
+    assert!(memory.len() >= params.blocks(), "Argon2 memory too small");
+    assert!(out.len() >= 4, "Argon2 output too short");
+    let memory = &mut memory[..params.blocks()];
+    let lanes = params.parallelism as usize;
+    let lane_length = memory.len() / lanes;
+
+    let mut initial = [0u8; BLAKE2B_MAX_DIGEST_SIZE];
+    let mut hash = Blake2b::new(BLAKE2B_MAX_DIGEST_SIZE);
+    for value in [params.parallelism, out.len() as u32, params.memory_kib, params.iterations, VERSION, TYPE_ID] {
+        hash.update(&value.to_le_bytes());
+    }
+    for input in [password, salt, secret, associated] {
+        hash.update(&(input.len() as u32).to_le_bytes());
+        hash.update(input);
+    }
+    hash.finalize(&mut initial);
+
+    let mut bytes = [0u8; 8 * BLOCK_WORDS];
+    for lane in 0..lanes {
+        for column in 0..2u32 {
+            hash_long(&[&initial, &column.to_le_bytes(), &(lane as u32).to_le_bytes()], &mut bytes);
+            let block = &mut memory[lane * lane_length + column as usize];
+            for (word, chunk) in block.iter_mut().zip(bytes.chunks_exact(8)) {
+                *word = u64::from_le_bytes(chunk.try_into().unwrap());
+            }
+        }
+    }
+
+    for pass in 0..params.iterations as usize {
+        for slice in 0..SYNC_POINTS {
+            for lane in 0..lanes {
+                fill_segment(memory, params, &mut Position { pass, lane, slice, index: 0 });
+            }
+        }
+    }
+
+    let mut last = memory[lane_length - 1];
+    for lane in 1..lanes {
+        for (word, other) in last.iter_mut().zip(memory[lane * lane_length + lane_length - 1].iter()) {
+            *word ^= other;
+        }
+    }
+    for (chunk, word) in bytes.chunks_exact_mut(8).zip(last.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    hash_long(&[&bytes], out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_reference_tags() {
+        // RFC 9106, section 5.3
+        let params = Params { memory_kib: 32, iterations: 3, parallelism: 4 };
+        let mut memory = [[0u64; BLOCK_WORDS]; 32];
+        let mut tag = [0u8; 32];
+        argon2id(&[0x01; 32], &[0x02; 16], &[0x03; 8], &[0x04; 12], &params, &mut memory, &mut tag);
+        assert_eq!(tag[..8], [0x0d, 0x64, 0x0d, 0xf5, 0x8d, 0x78, 0x76, 0x6c]);
+        assert_eq!(tag[24..], [0xb5, 0x25, 0x20, 0xe9, 0x6b, 0x01, 0xe6, 0x59]);
+
+        // Two lanes over two passes, without secret or associated data
+        let params = Params { memory_kib: 64, iterations: 2, parallelism: 2 };
+        let mut memory = [[0u64; BLOCK_WORDS]; 64];
+        argon2id(b"correct horse battery staple", b"orion-salt-0001!", &[], &[], &params, &mut memory, &mut tag);
+        assert_eq!(tag[..8], [0x18, 0x54, 0x50, 0x31, 0xbc, 0x33, 0xb1, 0x13]);
+        assert_eq!(tag[24..], [0x7b, 0x61, 0xb3, 0xc2, 0xc6, 0x90, 0x7e, 0xe2]);
+    }
+
+    #[test]
+    fn test_validates_parameters() {
+        assert!(Params { memory_kib: 19456, iterations: 2, parallelism: 1 }.is_valid());
+        assert!(!Params { memory_kib: 16, iterations: 1, parallelism: 4 }.is_valid());
+        assert!(!Params { memory_kib: 64, iterations: 0, parallelism: 1 }.is_valid());
+        assert_eq!(Params { memory_kib: 37, iterations: 1, parallelism: 2 }.blocks(), 32);
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * Orion Operating System - BLAKE2b
+ *
+ * BLAKE2b (RFC 7693), unkeyed, with any digest length up to 64 bytes.
+ * Argon2 is built on it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+pub const BLAKE2B_MAX_DIGEST_SIZE: usize = 64;
+
+const BLOCK_SIZE: usize = 128;
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 12] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+];
+
+/// Streaming BLAKE2b with a digest of 1 to 64 bytes
+#[derive(Clone)]
+pub struct Blake2b {
+    state: [u64; 8],
+    buffer: [u8; BLOCK_SIZE],
+    buffered: usize,
+    counter: u128,
+    digest_size: usize,
+}
+
+fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+impl Blake2b {
+    /// Panics unless `digest_size` is 1 to BLAKE2B_MAX_DIGEST_SIZE
+    pub fn new(digest_size: usize) -> Self {
+        assert!((1..=BLAKE2B_MAX_DIGEST_SIZE).contains(&digest_size));
+        let mut state = IV;
+        state[0] ^= 0x0101_0000 ^ digest_size as u64;
+        Self { state, buffer: [0; BLOCK_SIZE], buffered: 0, counter: 0, digest_size }
+    }
+
+    fn compress(&mut self, last: bool) {
+        let mut m = [0u64; 16];
+        for (word, bytes) in m.iter_mut().zip(self.buffer.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let mut v = [0u64; 16];
+        v[..8].copy_from_slice(&self.state);
+        v[8..].copy_from_slice(&IV);
+        v[12] ^= self.counter as u64;
+        v[13] ^= (self.counter >> 64) as u64;
+        if last {
+            v[14] = !v[14];
+        }
+        for s in SIGMA.iter() {
+            mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+        for (index, word) in self.state.iter_mut().enumerate() {
+            *word ^= v[index] ^ v[index + 8];
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // The last block is only compressed by finalize
+            if self.buffered == BLOCK_SIZE {
+                self.counter += BLOCK_SIZE as u128;
+                self.compress(false);
+                self.buffered = 0;
+            }
+            let count = data.len().min(BLOCK_SIZE - self.buffered);
+            self.buffer[self.buffered..self.buffered + count].copy_from_slice(&data[..count]);
+            self.buffered += count;
+            data = &data[count..];
+        }
+    }
+
+    /// Write the digest to `out`, which must be as long as the digest
+    pub fn finalize(mut self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.digest_size);
+        self.counter += self.buffered as u128;
+        self.buffer[self.buffered..].fill(0);
+        self.compress(true);
+        let mut digest = [0u8; BLAKE2B_MAX_DIGEST_SIZE];
+        for (bytes, word) in digest.chunks_exact_mut(8).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        out.copy_from_slice(&digest[..self.digest_size]);
+    }
+
+    pub fn digest(data: &[u8], out: &mut [u8]) {
+        let mut hash = Self::new(out.len());
+        hash.update(data);
+        hash.finalize(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> [u8; 128] {
+        let mut out = [b' '; 128];
+        for (index, byte) in bytes.iter().enumerate() {
+            out[2 * index] = b"0123456789abcdef"[(byte >> 4) as usize];
+            out[2 * index + 1] = b"0123456789abcdef"[(byte & 15) as usize];
+        }
+        out
+    }
+
+    #[test]
+    fn test_matches_reference_digests() {
+        let mut digest = [0u8; 64];
+        Blake2b::digest(b"abc", &mut digest);
+        assert_eq!(
+            &hex(&digest)[..],
+            &b"ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+               7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"[..]
+        );
+
+        let mut short = [0u8; 32];
+        Blake2b::digest(b"", &mut short);
+        assert_eq!(&hex(&short)[..64], b"0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8");
+
+        // Two whole blocks, fed unevenly
+        let mut data = [0u8; 512];
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        let mut hash = Blake2b::new(64);
+        hash.update(&data[..100]);
+        hash.update(&data[100..256]);
+        hash.update(&data[256..]);
+        hash.finalize(&mut digest);
+        assert_eq!(
+            &hex(&digest)[..],
+            &b"c59ab1095ca4579525338b6b74689ff234bc3fe9765fe26dfb04ddceaee0ab84\
+               dfd8967594cb261fcd88687f4454d80f718116c1b3c32f9f7e169357468cbe67"[..]
+        );
+    }
+}
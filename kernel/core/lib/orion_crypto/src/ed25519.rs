@@ -0,0 +1,587 @@
+/*
+ * Orion Operating System - Ed25519
+ *
+ * Ed25519 signatures (RFC 8032). Verification only handles public data and
+ * favours clarity; signing runs on secret scalars and therefore goes
+ * through the constant-time ladder and reduction below.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::sha512::Sha512;
+
+pub const PUBLIC_KEY_SIZE: usize = 32;
+pub const SIGNATURE_SIZE: usize = 64;
+pub const SECRET_KEY_SIZE: usize = 32;
+
+// ========================================
+// FIELD ARITHMETIC MOD 2^255 - 19
+// ========================================
+
+const MASK51: u64 = (1 << 51) - 1;
+
+// Exponents used for inversion and square roots, little-endian
+const EXP_P_MINUS_2: [u8; 32] = exponent(0xeb, 0x7f);
+const EXP_P_MINUS_5_DIV_8: [u8; 32] = exponent(0xfd, 0x0f);
+const EXP_P_MINUS_1_DIV_4: [u8; 32] = exponent(0xfb, 0x1f);
+
+const fn exponent(low: u8, high: u8) -> [u8; 32] {
+    let mut bytes = [0xff; 32];
+    bytes[0] = low;
+    bytes[31] = high;
+    bytes
+}
+
+/// Field element in radix 2^51
+#[derive(Clone, Copy)]
+struct Fe([u64; 5]);
+
+impl Fe {
+    const ZERO: Fe = Fe([0; 5]);
+    const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+
+    fn from_u64(value: u64) -> Fe {
+        Fe([value & MASK51, value >> 51, 0, 0, 0])
+    }
+
+    /// Decode 255 bits, ignoring the top bit of the last byte
+    fn from_bytes(bytes: &[u8; 32]) -> Fe {
+        let word = |i: usize| {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+            u64::from_le_bytes(raw)
+        };
+        let (w0, w1, w2, w3) = (word(0), word(1), word(2), word(3));
+        Fe([
+            w0 & MASK51,
+            ((w0 >> 51) | (w1 << 13)) & MASK51,
+            ((w1 >> 38) | (w2 << 26)) & MASK51,
+            ((w2 >> 25) | (w3 << 39)) & MASK51,
+            (w3 >> 12) & MASK51,
+        ])
+    }
+
+    /// Canonical encoding, fully reduced mod p
+    fn to_bytes(self) -> [u8; 32] {
+        let mut h = self.carry().0;
+
+        let mut q = (h[0] + 19) >> 51;
+        q = (h[1] + q) >> 51;
+        q = (h[2] + q) >> 51;
+        q = (h[3] + q) >> 51;
+        q = (h[4] + q) >> 51;
+
+        h[0] += 19 * q;
+        for i in 0..4 {
+            h[i + 1] += h[i] >> 51;
+            h[i] &= MASK51;
+        }
+        h[4] &= MASK51;
+
+        let words = [
+            h[0] | (h[1] << 51),
+            (h[1] >> 13) | (h[2] << 38),
+            (h[2] >> 26) | (h[3] << 25),
+            (h[3] >> 39) | (h[4] << 12),
+        ];
+        let mut bytes = [0u8; 32];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Propagate carries so every limb fits in 52 bits
+    fn carry(&self) -> Fe {
+        let mut h = self.0;
+        for i in 0..4 {
+            h[i + 1] += h[i] >> 51;
+            h[i] &= MASK51;
+        }
+        h[0] += 19 * (h[4] >> 51);
+        h[4] &= MASK51;
+        Fe(h)
+    }
+
+    fn add(&self, other: &Fe) -> Fe {
+        let mut h = self.0;
+        for (limb, value) in h.iter_mut().zip(other.0.iter()) {
+            *limb += value;
+        }
+        Fe(h).carry()
+    }
+
+    fn sub(&self, other: &Fe) -> Fe {
+        // Add 4p first so no limb underflows
+        const FOUR_P: [u64; 5] = [
+            0x1fffffffffffb4,
+            0x1ffffffffffffc,
+            0x1ffffffffffffc,
+            0x1ffffffffffffc,
+            0x1ffffffffffffc,
+        ];
+        let mut h = self.0;
+        for i in 0..5 {
+            h[i] = h[i] + FOUR_P[i] - other.0[i];
+        }
+        Fe(h).carry()
+    }
+
+    fn neg(&self) -> Fe {
+        Fe::ZERO.sub(self)
+    }
+
+    fn mul(&self, other: &Fe) -> Fe {
+        let a = self.0.map(|limb| limb as u128);
+        let b = other.0.map(|limb| limb as u128);
+        let b19 = b.map(|limb| limb * 19);
+
+        let r = [
+            a[0] * b[0] + a[1] * b19[4] + a[2] * b19[3] + a[3] * b19[2] + a[4] * b19[1],
+            a[0] * b[1] + a[1] * b[0] + a[2] * b19[4] + a[3] * b19[3] + a[4] * b19[2],
+            a[0] * b[2] + a[1] * b[1] + a[2] * b[0] + a[3] * b19[4] + a[4] * b19[3],
+            a[0] * b[3] + a[1] * b[2] + a[2] * b[1] + a[3] * b[0] + a[4] * b19[4],
+            a[0] * b[4] + a[1] * b[3] + a[2] * b[2] + a[3] * b[1] + a[4] * b[0],
+        ];
+
+        let mut h = [0u64; 5];
+        let mut carry: u128 = 0;
+        for i in 0..5 {
+            let value = r[i] + carry;
+            h[i] = (value as u64) & MASK51;
+            carry = value >> 51;
+        }
+        let mut fe = Fe(h);
+        fe.0[0] += (carry as u64) * 19;
+        fe.carry()
+    }
+
+    fn square(&self) -> Fe {
+        self.mul(self)
+    }
+
+    /// Raise to a 256-bit little-endian exponent
+    fn pow(&self, exponent: &[u8; 32]) -> Fe {
+        let mut result = Fe::ONE;
+        for bit in (0..256).rev() {
+            result = result.square();
+            if (exponent[bit / 8] >> (bit % 8)) & 1 == 1 {
+                result = result.mul(self);
+            }
+        }
+        result
+    }
+
+    fn invert(&self) -> Fe {
+        self.pow(&EXP_P_MINUS_2)
+    }
+
+    fn equals(&self, other: &Fe) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+
+    fn is_negative(&self) -> bool {
+        self.to_bytes()[0] & 1 == 1
+    }
+
+    /// `other` when `choice` is 1, `self` when it is 0, without branching
+    fn select(&self, other: &Fe, choice: u64) -> Fe {
+        let mask = 0u64.wrapping_sub(choice);
+        let mut h = self.0;
+        for (limb, value) in h.iter_mut().zip(other.0.iter()) {
+            *limb ^= mask & (*limb ^ value);
+        }
+        Fe(h)
+    }
+}
+
+// ========================================
+// CURVE POINTS
+// ========================================
+
+struct CurveConstants {
+    d: Fe,
+    d2: Fe,
+    sqrt_m1: Fe,
+}
+
+impl CurveConstants {
+    fn new() -> Self {
+        // d = -121665 / 121666
+        let d = Fe::from_u64(121665).neg().mul(&Fe::from_u64(121666).invert());
+        Self {
+            d,
+            d2: d.add(&d),
+            sqrt_m1: Fe::from_u64(2).pow(&EXP_P_MINUS_1_DIV_4),
+        }
+    }
+}
+
+/// Point in extended twisted Edwards coordinates
+#[derive(Clone, Copy)]
+struct Point {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+    t: Fe,
+}
+
+impl Point {
+    const IDENTITY: Point = Point {
+        x: Fe::ZERO,
+        y: Fe::ONE,
+        z: Fe::ONE,
+        t: Fe::ZERO,
+    };
+
+    fn decompress(bytes: &[u8; 32], constants: &CurveConstants) -> Option<Point> {
+        let sign = bytes[31] >> 7 == 1;
+        let y = Fe::from_bytes(bytes);
+
+        // Reject non-canonical y (y >= p)
+        let mut canonical = *bytes;
+        canonical[31] &= 0x7f;
+        if y.to_bytes() != canonical {
+            return None;
+        }
+
+        let y2 = y.square();
+        let u = y2.sub(&Fe::ONE);
+        let v = constants.d.mul(&y2).add(&Fe::ONE);
+
+        // x = u v^3 (u v^7)^((p-5)/8)
+        let v3 = v.square().mul(&v);
+        let v7 = v3.square().mul(&v);
+        let mut x = u.mul(&v3).mul(&u.mul(&v7).pow(&EXP_P_MINUS_5_DIV_8));
+
+        let vx2 = v.mul(&x.square());
+        if !vx2.equals(&u) {
+            if !vx2.equals(&u.neg()) {
+                return None;
+            }
+            x = x.mul(&constants.sqrt_m1);
+        }
+
+        if x.equals(&Fe::ZERO) && sign {
+            return None;
+        }
+        if x.is_negative() != sign {
+            x = x.neg();
+        }
+
+        Some(Point { x, y, z: Fe::ONE, t: x.mul(&y) })
+    }
+
+    fn compress(&self) -> [u8; 32] {
+        let z_inv = self.z.invert();
+        let x = self.x.mul(&z_inv);
+        let mut bytes = self.y.mul(&z_inv).to_bytes();
+        bytes[31] |= (x.is_negative() as u8) << 7;
+        bytes
+    }
+
+    fn neg(&self) -> Point {
+        Point { x: self.x.neg(), y: self.y, z: self.z, t: self.t.neg() }
+    }
+
+    /// Unified addition (add-2008-hwcd-3), also valid for doubling
+    fn add(&self, other: &Point, constants: &CurveConstants) -> Point {
+        let a = self.y.sub(&self.x).mul(&other.y.sub(&other.x));
+        let b = self.y.add(&self.x).mul(&other.y.add(&other.x));
+        let c = self.t.mul(&constants.d2).mul(&other.t);
+        let d = self.z.add(&self.z).mul(&other.z);
+        let e = b.sub(&a);
+        let f = d.sub(&c);
+        let g = d.add(&c);
+        let h = b.add(&a);
+        Point { x: e.mul(&f), y: g.mul(&h), z: f.mul(&g), t: e.mul(&h) }
+    }
+
+    fn select(&self, other: &Point, choice: u64) -> Point {
+        Point {
+            x: self.x.select(&other.x, choice),
+            y: self.y.select(&other.y, choice),
+            z: self.z.select(&other.z, choice),
+            t: self.t.select(&other.t, choice),
+        }
+    }
+
+    /// Multiply by a secret scalar; every bit costs one doubling and one
+    /// addition regardless of its value
+    fn mul_scalar_ct(&self, scalar: &[u8; 32], constants: &CurveConstants) -> Point {
+        let mut result = Point::IDENTITY;
+        for bit in (0..256).rev() {
+            result = result.add(&result, constants);
+            let sum = result.add(self, constants);
+            result = result.select(&sum, ((scalar[bit / 8] >> (bit % 8)) & 1) as u64);
+        }
+        result
+    }
+
+    /// Multiply by a 256-bit little-endian scalar
+    fn mul_scalar(&self, scalar: &[u8; 32], constants: &CurveConstants) -> Point {
+        let mut result = Point::IDENTITY;
+        for bit in (0..256).rev() {
+            result = result.add(&result, constants);
+            if (scalar[bit / 8] >> (bit % 8)) & 1 == 1 {
+                result = result.add(self, constants);
+            }
+        }
+        result
+    }
+}
+
+// ========================================
+// SCALARS MOD L
+// ========================================
+
+/// Group order L = 2^252 + 27742317777372353535851937790883648493
+const GROUP_ORDER: [u64; 4] = [0x5812631a5cf5d3ed, 0x14def9dea2f79cd6, 0, 0x1000000000000000];
+
+/// Encoding of the base point B
+const BASE_POINT: [u8; 32] = {
+    let mut bytes = [0x66; 32];
+    bytes[0] = 0x58;
+    bytes
+};
+
+fn scalar_is_canonical(bytes: &[u8; 32]) -> bool {
+    for i in (0..4).rev() {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+        let limb = u64::from_le_bytes(raw);
+        if limb != GROUP_ORDER[i] {
+            return limb < GROUP_ORDER[i];
+        }
+    }
+    false
+}
+
+/// Reduce a 512-bit little-endian value mod L by binary long division.
+/// The subtraction is always computed and kept by mask, so the running time
+/// does not depend on the value (nonces and secret scalars pass here).
+fn scalar_reduce(wide: &[u8; 64]) -> [u8; 32] {
+    let mut r = [0u64; 5];
+
+    for bit in (0..512).rev() {
+        // r = 2r + bit
+        let mut carry = ((wide[bit / 8] >> (bit % 8)) & 1) as u64;
+        for limb in r.iter_mut() {
+            let next = *limb >> 63;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+
+        // r < 2L, so one conditional subtraction is enough
+        let mut difference = [0u64; 5];
+        let mut borrow = 0u64;
+        for i in 0..5 {
+            let order = if i < 4 { GROUP_ORDER[i] } else { 0 };
+            let (value, b1) = r[i].overflowing_sub(order);
+            let (value, b2) = value.overflowing_sub(borrow);
+            difference[i] = value;
+            borrow = (b1 | b2) as u64;
+        }
+        let keep = borrow.wrapping_sub(1);
+        for (limb, value) in r.iter_mut().zip(difference.iter()) {
+            *limb ^= keep & (*limb ^ value);
+        }
+    }
+
+    let mut bytes = [0u8; 32];
+    for (chunk, limb) in bytes.chunks_exact_mut(8).zip(r.iter()) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    bytes
+}
+
+/// (a * b + c) mod L for 256-bit little-endian a, b and c < L
+fn scalar_mul_add(a: &[u8; 32], b: &[u8; 32], c: &[u8; 32]) -> [u8; 32] {
+    let limbs = |bytes: &[u8; 32]| {
+        let mut out = [0u64; 4];
+        for (limb, chunk) in out.iter_mut().zip(bytes.chunks_exact(8)) {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(chunk);
+            *limb = u64::from_le_bytes(raw);
+        }
+        out
+    };
+    let (a, b, c) = (limbs(a), limbs(b), limbs(c));
+
+    let mut wide = [0u64; 8];
+    wide[..4].copy_from_slice(&c);
+    for i in 0..4 {
+        let mut carry: u128 = 0;
+        for j in 0..4 {
+            let value = wide[i + j] as u128 + (a[i] as u128) * (b[j] as u128) + carry;
+            wide[i + j] = value as u64;
+            carry = value >> 64;
+        }
+        let mut k = i + 4;
+        while carry != 0 && k < 8 {
+            let value = wide[k] as u128 + carry;
+            wide[k] = value as u64;
+            carry = value >> 64;
+            k += 1;
+        }
+    }
+
+    let mut bytes = [0u8; 64];
+    for (chunk, limb) in bytes.chunks_exact_mut(8).zip(wide.iter()) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    scalar_reduce(&bytes)
+}
+
+// ========================================
+// SIGNING
+// ========================================
+
+/// Clamped secret scalar and nonce prefix derived from a 32-byte seed
+fn expand_secret(secret_key: &[u8; SECRET_KEY_SIZE]) -> ([u8; 32], [u8; 32]) {
+    let hash = Sha512::digest(secret_key);
+    let mut scalar = [0u8; 32];
+    let mut prefix = [0u8; 32];
+    scalar.copy_from_slice(&hash[..32]);
+    prefix.copy_from_slice(&hash[32..]);
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    (scalar, prefix)
+}
+
+fn base_point(constants: &CurveConstants) -> Point {
+    match Point::decompress(&BASE_POINT, constants) {
+        Some(point) => point,
+        None => unreachable!(),
+    }
+}
+
+/// Public key belonging to a 32-byte secret key (seed)
+pub fn public_key(secret_key: &[u8; SECRET_KEY_SIZE]) -> [u8; PUBLIC_KEY_SIZE] {
+    let constants = CurveConstants::new();
+    let (scalar, _) = expand_secret(secret_key);
+    base_point(&constants).mul_scalar_ct(&scalar, &constants).compress()
+}
+
+/// Deterministic Ed25519 signature of `message`
+pub fn sign(secret_key: &[u8; SECRET_KEY_SIZE], message: &[u8]) -> [u8; SIGNATURE_SIZE] {
+    let constants = CurveConstants::new();
+    let base = base_point(&constants);
+    let (scalar, prefix) = expand_secret(secret_key);
+    let public_key = base.mul_scalar_ct(&scalar, &constants).compress();
+
+    let mut hasher = Sha512::new();
+    hasher.update(&prefix);
+    hasher.update(message);
+    let nonce = scalar_reduce(&hasher.finalize());
+    let r_bytes = base.mul_scalar_ct(&nonce, &constants).compress();
+
+    let mut hasher = Sha512::new();
+    hasher.update(&r_bytes);
+    hasher.update(&public_key);
+    hasher.update(message);
+    let k = scalar_reduce(&hasher.finalize());
+
+    let mut signature = [0u8; SIGNATURE_SIZE];
+    signature[..32].copy_from_slice(&r_bytes);
+    signature[32..].copy_from_slice(&scalar_mul_add(&k, &scalar, &nonce));
+    signature
+}
+
+// ========================================
+// VERIFICATION
+// ========================================
+
+/// Check an Ed25519 signature; accepts iff [S]B == R + [k]A
+pub fn verify(public_key: &[u8; PUBLIC_KEY_SIZE], message: &[u8], signature: &[u8; SIGNATURE_SIZE]) -> bool {
+    let constants = CurveConstants::new();
+
+    let mut r_bytes = [0u8; 32];
+    let mut s_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    s_bytes.copy_from_slice(&signature[32..]);
+
+    if !scalar_is_canonical(&s_bytes) {
+        return false;
+    }
+    let a = match Point::decompress(public_key, &constants) {
+        Some(point) => point,
+        None => return false,
+    };
+    let base = base_point(&constants);
+
+    let mut hasher = Sha512::new();
+    hasher.update(&r_bytes);
+    hasher.update(public_key);
+    hasher.update(message);
+    let k = scalar_reduce(&hasher.finalize());
+
+    let sb = base.mul_scalar(&s_bytes, &constants);
+    let ka = a.neg().mul_scalar(&k, &constants);
+    sb.add(&ka, &constants).compress() == r_bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex<const N: usize>(text: &str) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        bytes
+    }
+
+    /// RFC 8032 section 7.1, test 2 (one byte message 0x72)
+    fn rfc8032_test2() -> ([u8; 32], [u8; 64]) {
+        (
+            hex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c"),
+            hex("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+                 085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"),
+        )
+    }
+
+    #[test]
+    fn test_verifies_rfc8032_vectors() {
+        let public_key = hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        let signature = hex("e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+                             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b");
+        assert!(verify(&public_key, b"", &signature));
+
+        let (public_key, signature) = rfc8032_test2();
+        assert!(verify(&public_key, &[0x72], &signature));
+    }
+
+    #[test]
+    fn test_rejects_forgeries() {
+        let (public_key, signature) = rfc8032_test2();
+        assert!(!verify(&public_key, &[0x73], &signature));
+
+        let mut tampered = signature;
+        tampered[10] ^= 1;
+        assert!(!verify(&public_key, &[0x72], &tampered));
+
+        // S >= L is rejected outright
+        let mut high_s = signature;
+        high_s[63] = 0xff;
+        assert!(!verify(&public_key, &[0x72], &high_s));
+    }
+
+    #[test]
+    fn test_signs_rfc8032_vectors() {
+        let secret_key = hex("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb");
+        let (expected_key, expected_signature) = rfc8032_test2();
+        assert_eq!(public_key(&secret_key), expected_key);
+        assert_eq!(sign(&secret_key, &[0x72]), expected_signature);
+
+        let secret_key = hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+        let signature = sign(&secret_key, b"TLS 1.3, server CertificateVerify");
+        assert!(verify(&public_key(&secret_key), b"TLS 1.3, server CertificateVerify", &signature));
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * Orion Operating System - HMAC and HKDF
+ *
+ * HMAC-SHA512 (RFC 2104) and HKDF-SHA512 (RFC 5869). The keyring derives
+ * keys for services from the keys it holds with them, and file encryption
+ * derives one key per file from the key of its user.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::sha512::{Sha512, SHA512_DIGEST_SIZE};
+
+const BLOCK_SIZE: usize = 128;
+
+/// Longest HKDF output
+pub const HKDF_MAX_OUTPUT: usize = 255 * SHA512_DIGEST_SIZE;
+
+/// Streaming HMAC-SHA512
+pub struct HmacSha512 {
+    inner: Sha512,
+    outer: Sha512,
+}
+
+impl HmacSha512 {
+    pub fn new(key: &[u8]) -> Self {
+        let mut padded = [0u8; BLOCK_SIZE];
+        if key.len() > BLOCK_SIZE {
+            padded[..SHA512_DIGEST_SIZE].copy_from_slice(&Sha512::digest(key));
+        } else {
+            padded[..key.len()].copy_from_slice(key);
+        }
+        let mut inner = Sha512::new();
+        inner.update(&padded.map(|byte| byte ^ 0x36));
+        let mut outer = Sha512::new();
+        outer.update(&padded.map(|byte| byte ^ 0x5c));
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(mut self) -> [u8; SHA512_DIGEST_SIZE] {
+        self.outer.update(&self.inner.finalize());
+        self.outer.finalize()
+    }
+}
+
+pub fn hmac_sha512(key: &[u8], message: &[u8]) -> [u8; SHA512_DIGEST_SIZE] {
+    let mut hmac = HmacSha512::new(key);
+    hmac.update(message);
+    hmac.finalize()
+}
+
+/// Fill `out` with key material derived from `secret`, bound to `info`.
+/// An empty salt stands for zeros, as the RFC has it
+pub fn hkdf_sha512(salt: &[u8], secret: &[u8], info: &[u8], out: &mut [u8]) {
+    assert!(out.len() <= HKDF_MAX_OUTPUT, "HKDF output too long");
+    let pseudorandom = hmac_sha512(salt, secret);
+
+    let mut previous = [0u8; SHA512_DIGEST_SIZE];
+    for (index, chunk) in out.chunks_mut(SHA512_DIGEST_SIZE).enumerate() {
+        let mut hmac = HmacSha512::new(&pseudorandom);
+        if index > 0 {
+            hmac.update(&previous);
+        }
+        hmac.update(info);
+        hmac.update(&[index as u8 + 1]);
+        previous = hmac.finalize();
+        chunk.copy_from_slice(&previous[..chunk.len()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_rfc_vectors() {
+        // RFC 4231, test case 2
+        let mac = hmac_sha512(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(mac[..8], [0x16, 0x4b, 0x7a, 0x7b, 0xfc, 0xf8, 0x19, 0xe2]);
+        assert_eq!(mac[56..], [0x63, 0x6e, 0x07, 0x0a, 0x38, 0xbc, 0xe7, 0x37]);
+
+        // Longer than one digest, without salt
+        let mut out = [0u8; 80];
+        hkdf_sha512(&[], &[0x0b; 22], b"info", &mut out);
+        assert_eq!(out[..8], [0x00, 0xcc, 0x2b, 0xf4, 0x5a, 0xb6, 0x01, 0x13]);
+        assert_eq!(out[72..], [0xc7, 0x62, 0x6d, 0xa7, 0x5d, 0xd3, 0x5d, 0x3b]);
+    }
+}
@@ -0,0 +1,35 @@
+/*
+ * Orion Operating System - Cryptographic Primitives
+ *
+ * Primitives shared by the user-space servers: the I/O server checks
+ * driver signatures with them, the keyring signs on behalf of services
+ * that hold a USE grant on a private key and downloads are checked against
+ * their published SHA-256 digests. The fs server encrypts the files of
+ * encrypted directories with AES-256 under keys derived by HKDF, and the
+ * identity service hashes passwords with Argon2id. Key material they are
+ * done with is erased with `wipe`.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+pub mod aes;
+pub mod argon2;
+pub mod blake2b;
+pub mod ed25519;
+pub mod kdf;
+pub mod sha256;
+pub mod sha512;
+
+/// Zero a buffer in a way the optimizer cannot elide
+pub fn wipe(buffer: &mut [u8]) {
+    for byte in buffer.iter_mut() {
+        unsafe { core::ptr::write_volatile(byte, 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
@@ -0,0 +1,192 @@
+/*
+ * Orion Operating System - Lock Manager Client
+ *
+ * Client side of the dlm protocol for storage services. The transport is
+ * a trait so the replication code can call through its IPC channel and
+ * tests through an in-process server; a call blocks until the reply
+ * arrives, which for a waiting LOCK means until it is settled.
+ *
+ * A node must renew its lease well within the period RENEW reports. When
+ * a renewal comes back restarted, every lock the node held is gone and
+ * may already belong to someone else: the caller has to stop writing and
+ * reacquire. Writers should hand the fencing token of their lock to the
+ * storage target so that a stale holder is refused even then.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::lock::{DlmError, Holder, LockMode};
+use crate::protocol::{decode_holders, read_u32, read_u64, status_error, DlmRequest, STATUS_OK};
+
+/// Carries a request to the dlm server and returns its reply
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockHandle {
+    pub lock: u64,
+    /// Increases with every grant on the resource
+    pub fence: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lease {
+    pub duration_ms: u32,
+    /// The previous lease was lost along with every lock it covered
+    pub restarted: bool,
+}
+
+pub struct DlmClient<T: Transport> {
+    transport: T,
+    node: u64,
+}
+
+impl<T: Transport> DlmClient<T> {
+    pub fn new(transport: T, node: u64) -> Self {
+        Self { transport, node }
+    }
+
+    pub fn node(&self) -> u64 {
+        self.node
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, request: DlmRequest) -> Result<Vec<u8>, DlmError> {
+        let response = self.transport.call(&request.encode()).ok_or(DlmError::Unavailable)?;
+        let status = read_u32(&response, 0).ok_or(DlmError::Unavailable)? as i32;
+        if status != STATUS_OK {
+            return Err(status_error(status));
+        }
+        Ok(response[4..].to_vec())
+    }
+
+    pub fn open_namespace(&mut self, name: &str) -> Result<u32, DlmError> {
+        let payload = self.call(DlmRequest::OpenNamespace { name: String::from(name) })?;
+        read_u32(&payload, 0).ok_or(DlmError::Unavailable)
+    }
+
+    fn acquire(
+        &mut self,
+        namespace: u32,
+        resource: &[u8],
+        mode: LockMode,
+        nowait: bool,
+        timeout_ms: u32,
+    ) -> Result<LockHandle, DlmError> {
+        let request =
+            DlmRequest::Lock { namespace, node: self.node, mode, nowait, timeout_ms, resource: resource.to_vec() };
+        let payload = self.call(request)?;
+        Ok(LockHandle {
+            lock: read_u64(&payload, 0).ok_or(DlmError::Unavailable)?,
+            fence: read_u64(&payload, 8).ok_or(DlmError::Unavailable)?,
+        })
+    }
+
+    /// Wait for a lock; a `timeout_ms` of 0 waits until granted
+    pub fn lock(
+        &mut self,
+        namespace: u32,
+        resource: &[u8],
+        mode: LockMode,
+        timeout_ms: u32,
+    ) -> Result<LockHandle, DlmError> {
+        self.acquire(namespace, resource, mode, false, timeout_ms)
+    }
+
+    /// Take a lock only if no conflicting one is held or queued
+    pub fn try_lock(&mut self, namespace: u32, resource: &[u8], mode: LockMode) -> Result<LockHandle, DlmError> {
+        self.acquire(namespace, resource, mode, true, 0)
+    }
+
+    pub fn unlock(&mut self, handle: LockHandle) -> Result<(), DlmError> {
+        self.call(DlmRequest::Unlock { lock: handle.lock }).map(|_| ())
+    }
+
+    /// Change a held lock's mode; an upgrade fails with WouldBlock unless
+    /// the caller is the only holder, and gives the handle a new fence
+    pub fn convert(&mut self, handle: &mut LockHandle, mode: LockMode) -> Result<(), DlmError> {
+        let payload = self.call(DlmRequest::Convert { lock: handle.lock, mode })?;
+        handle.fence = read_u64(&payload, 0).ok_or(DlmError::Unavailable)?;
+        Ok(())
+    }
+
+    pub fn renew(&mut self) -> Result<Lease, DlmError> {
+        let payload = self.call(DlmRequest::Renew { node: self.node })?;
+        Ok(Lease {
+            duration_ms: read_u32(&payload, 0).ok_or(DlmError::Unavailable)?,
+            restarted: read_u32(&payload, 4).ok_or(DlmError::Unavailable)? != 0,
+        })
+    }
+
+    pub fn holders(&mut self, namespace: u32, resource: &[u8]) -> Result<Vec<Holder>, DlmError> {
+        let payload = self.call(DlmRequest::Query { namespace, resource: resource.to_vec() })?;
+        decode_holders(&payload).ok_or(DlmError::Unavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::LockManager;
+    use crate::server::{handle, Outbox};
+    use core::cell::RefCell;
+
+    /// In-process server shared by several clients; `now_ns` is the clock
+    struct Cluster {
+        manager: RefCell<LockManager>,
+        now_ns: core::cell::Cell<u64>,
+    }
+
+    struct Local<'a> {
+        cluster: &'a Cluster,
+        pid: u64,
+    }
+
+    impl Transport for Local<'_> {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            let mut outbox = Outbox::new();
+            let request = DlmRequest::decode(request)?;
+            handle(&mut self.cluster.manager.borrow_mut(), self.pid, request, self.cluster.now_ns.get(), &mut outbox);
+            outbox.into_iter().find(|(recipient, _)| *recipient == self.pid).map(|(_, message)| message)
+        }
+    }
+
+    #[test]
+    fn test_clients_share_and_fence() {
+        let cluster = Cluster { manager: RefCell::new(LockManager::new(2_000_000_000)), now_ns: Default::default() };
+        let mut a = DlmClient::new(Local { cluster: &cluster, pid: 100 }, 1);
+        let mut b = DlmClient::new(Local { cluster: &cluster, pid: 200 }, 2);
+
+        let pool = a.open_namespace("pool").unwrap();
+        assert_eq!(b.open_namespace("pool"), Ok(pool));
+        let mut held = a.lock(pool, b"vol", LockMode::Shared, 0).unwrap();
+        let shared = b.try_lock(pool, b"vol", LockMode::Shared).unwrap();
+        assert_eq!(a.holders(pool, b"vol").unwrap().len(), 2);
+
+        assert_eq!(a.convert(&mut held, LockMode::Exclusive), Err(DlmError::WouldBlock));
+        assert_eq!(a.unlock(shared), Err(DlmError::NotOwner));
+        b.unlock(shared).unwrap();
+        let fence = held.fence;
+        a.convert(&mut held, LockMode::Exclusive).unwrap();
+        assert!(held.fence > fence);
+        assert_eq!(b.try_lock(pool, b"vol", LockMode::Shared), Err(DlmError::WouldBlock));
+
+        // Node 1 stops renewing; node 2 takes over with a higher fence
+        cluster.now_ns.set(1_500_000_000);
+        assert_eq!(b.renew(), Ok(Lease { duration_ms: 2000, restarted: false }));
+        cluster.now_ns.set(2_500_000_000);
+        assert_eq!(b.try_lock(pool, b"vol", LockMode::Shared), Err(DlmError::WouldBlock));
+        cluster.manager.borrow_mut().expire(cluster.now_ns.get());
+        let takeover = b.try_lock(pool, b"vol", LockMode::Exclusive).unwrap();
+        assert!(takeover.fence > held.fence);
+        assert!(a.renew().unwrap().restarted);
+    }
+}
@@ -0,0 +1,34 @@
+/*
+ * Orion Operating System - Distributed Lock Manager
+ *
+ * Locks that let several nodes share a storage pool or a replicated volume
+ * without stepping on each other. Locks live in named namespaces and are
+ * taken shared or exclusive on opaque resource names; every grant carries
+ * a fencing token so storage targets can turn away writes from a holder
+ * that lost its lock. Each node holds a lease it keeps renewing: when the
+ * lease runs out, or the cluster membership layer reports the node down,
+ * every lock of the node is released.
+ *
+ * The lock manager itself runs in the dlm server; this crate holds its
+ * state machine, the IPC protocol and the client API used by the storage
+ * replication code and clustered file systems.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod client;
+pub mod lock;
+pub mod protocol;
+pub mod server;
+
+pub use client::{DlmClient, Lease, LockHandle, Transport};
+pub use lock::{Acquired, DlmError, Event, Holder, LockManager, LockMode};
+pub use protocol::DlmRequest;
@@ -0,0 +1,511 @@
+/*
+ * Orion Operating System - Lock Table
+ *
+ * Requests on a resource are served in arrival order: a request is
+ * granted when it is compatible with every holder and nobody queued
+ * before it, so a stream of shared lockers cannot starve an exclusive
+ * one. Conversions never wait: a downgrade always succeeds, an upgrade
+ * only for the sole holder, which rules out conversion deadlocks.
+ *
+ * A node's lease starts with its first request and is extended by every
+ * renewal. Once it lapses the node is treated as failed: its locks are
+ * dropped, its queued requests fail, and the next renewal starts a new
+ * lease reported as restarted so the node knows its locks are gone.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const MAX_NAMESPACES: usize = 64;
+pub const MAX_NAME_LEN: usize = 64;
+pub const MAX_RESOURCE_LEN: usize = 256;
+/// Granted and queued requests across all namespaces
+pub const MAX_LOCKS: usize = 4096;
+
+pub const DEFAULT_LEASE_NS: u64 = 10_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+impl LockMode {
+    fn compatible(self, other: LockMode) -> bool {
+        self == LockMode::Shared && other == LockMode::Shared
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DlmError {
+    NotFound,
+    /// Conflicting lock held and the request may not wait
+    WouldBlock,
+    NotOwner,
+    /// The node's lease lapsed; it must renew, and its locks are gone
+    LeaseLost,
+    TimedOut,
+    Invalid,
+    NoSpace,
+    /// Server unreachable or reply malformed
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Acquired {
+    Granted {
+        lock: u64,
+        fence: u64,
+    },
+    /// Waiting behind conflicting requests; an Event tells how it ends
+    Queued {
+        lock: u64,
+    },
+}
+
+/// Outcome of a queued request, to be reported to its owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Granted { owner: u64, lock: u64, fence: u64 },
+    Failed { owner: u64, lock: u64, error: DlmError },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Holder {
+    pub node: u64,
+    pub mode: LockMode,
+    pub fence: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Granted { fence: u64 },
+    Waiting { deadline_ns: Option<u64> },
+}
+
+struct Lock {
+    namespace: u32,
+    resource: Vec<u8>,
+    node: u64,
+    owner: u64,
+    mode: LockMode,
+    state: State,
+}
+
+#[derive(Default)]
+struct Resource {
+    granted: Vec<u64>,
+    waiting: VecDeque<u64>,
+}
+
+struct Namespace {
+    name: String,
+    resources: BTreeMap<Vec<u8>, Resource>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NodeLease {
+    expiry_ns: u64,
+    /// Lapsed or reported down since the last renewal
+    lost: bool,
+}
+
+pub struct LockManager {
+    namespaces: Vec<Namespace>,
+    locks: BTreeMap<u64, Lock>,
+    leases: BTreeMap<u64, NodeLease>,
+    lease_ns: u64,
+    next_lock: u64,
+    next_fence: u64,
+}
+
+/// Grant queued requests from the front while they are compatible
+fn promote(resource: &mut Resource, locks: &mut BTreeMap<u64, Lock>, next_fence: &mut u64, events: &mut Vec<Event>) {
+    while let Some(&candidate) = resource.waiting.front() {
+        let mode = locks[&candidate].mode;
+        if !resource.granted.iter().all(|id| locks[id].mode.compatible(mode)) {
+            break;
+        }
+        resource.waiting.pop_front();
+        resource.granted.push(candidate);
+        let fence = *next_fence;
+        *next_fence += 1;
+        let lock = locks.get_mut(&candidate).unwrap();
+        lock.state = State::Granted { fence };
+        events.push(Event::Granted { owner: lock.owner, lock: candidate, fence });
+    }
+}
+
+impl LockManager {
+    pub fn new(lease_ns: u64) -> Self {
+        Self {
+            namespaces: Vec::new(),
+            locks: BTreeMap::new(),
+            leases: BTreeMap::new(),
+            lease_ns,
+            next_lock: 1,
+            next_fence: 1,
+        }
+    }
+
+    pub fn lease_ns(&self) -> u64 {
+        self.lease_ns
+    }
+
+    /// Identifier of a namespace, created on first use
+    pub fn open_namespace(&mut self, name: &str) -> Result<u32, DlmError> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(DlmError::Invalid);
+        }
+        if let Some(index) = self.namespaces.iter().position(|namespace| namespace.name == name) {
+            return Ok(index as u32 + 1);
+        }
+        if self.namespaces.len() == MAX_NAMESPACES {
+            return Err(DlmError::NoSpace);
+        }
+        self.namespaces.push(Namespace { name: String::from(name), resources: BTreeMap::new() });
+        Ok(self.namespaces.len() as u32)
+    }
+
+    fn namespace_mut(&mut self, namespace: u32) -> Result<&mut Namespace, DlmError> {
+        let index = (namespace as usize).checked_sub(1).ok_or(DlmError::NotFound)?;
+        self.namespaces.get_mut(index).ok_or(DlmError::NotFound)
+    }
+
+    /// Start the lease of a node new to the manager; refuse nodes whose
+    /// lease lapsed until they renew
+    fn check_lease(&mut self, node: u64, now_ns: u64) -> Result<(), DlmError> {
+        let lease_ns = self.lease_ns;
+        let lease = self.leases.entry(node).or_insert(NodeLease { expiry_ns: now_ns + lease_ns, lost: false });
+        if lease.lost || lease.expiry_ns <= now_ns {
+            return Err(DlmError::LeaseLost);
+        }
+        Ok(())
+    }
+
+    /// Request a lock for `owner` on behalf of `node`. A conflicting
+    /// request fails with WouldBlock unless `wait` is set, in which case it
+    /// is queued until granted, `deadline_ns` passes or the node fails.
+    #[allow(clippy::too_many_arguments)]
+    pub fn lock(
+        &mut self,
+        owner: u64,
+        namespace: u32,
+        resource: &[u8],
+        node: u64,
+        mode: LockMode,
+        wait: bool,
+        deadline_ns: Option<u64>,
+        now_ns: u64,
+    ) -> Result<Acquired, DlmError> {
+        if resource.is_empty() || resource.len() > MAX_RESOURCE_LEN {
+            return Err(DlmError::Invalid);
+        }
+        self.namespace_mut(namespace)?;
+        if self.locks.len() == MAX_LOCKS {
+            return Err(DlmError::NoSpace);
+        }
+        self.check_lease(node, now_ns)?;
+
+        let id = self.next_lock;
+        let locks = &self.locks;
+        let index = namespace as usize - 1;
+        let entry = self.namespaces[index].resources.entry(resource.to_vec()).or_default();
+        let grantable = entry.waiting.is_empty() && entry.granted.iter().all(|id| locks[id].mode.compatible(mode));
+        let (state, acquired) = if grantable {
+            let fence = self.next_fence;
+            self.next_fence += 1;
+            entry.granted.push(id);
+            (State::Granted { fence }, Acquired::Granted { lock: id, fence })
+        } else if wait {
+            entry.waiting.push_back(id);
+            (State::Waiting { deadline_ns }, Acquired::Queued { lock: id })
+        } else {
+            return Err(DlmError::WouldBlock);
+        };
+
+        self.next_lock += 1;
+        self.locks.insert(id, Lock { namespace, resource: resource.to_vec(), node, owner, mode, state });
+        Ok(acquired)
+    }
+
+    /// Drop a lock, or cancel a queued request, from the map and its resource
+    fn remove(&mut self, id: u64, events: &mut Vec<Event>) -> Option<Lock> {
+        let lock = self.locks.remove(&id)?;
+        let namespace = &mut self.namespaces[lock.namespace as usize - 1];
+        if let Some(resource) = namespace.resources.get_mut(&lock.resource) {
+            resource.granted.retain(|&held| held != id);
+            resource.waiting.retain(|&queued| queued != id);
+            promote(resource, &mut self.locks, &mut self.next_fence, events);
+            if resource.granted.is_empty() && resource.waiting.is_empty() {
+                namespace.resources.remove(&lock.resource);
+            }
+        }
+        Some(lock)
+    }
+
+    /// Release a lock or cancel a queued request
+    pub fn unlock(&mut self, owner: u64, lock: u64) -> Result<Vec<Event>, DlmError> {
+        match self.locks.get(&lock) {
+            None => return Err(DlmError::NotFound),
+            Some(held) if held.owner != owner => return Err(DlmError::NotOwner),
+            Some(_) => {}
+        }
+        let mut events = Vec::new();
+        self.remove(lock, &mut events);
+        Ok(events)
+    }
+
+    /// Change the mode of a granted lock; returns its fencing token, new
+    /// after an upgrade
+    pub fn convert(&mut self, owner: u64, lock: u64, mode: LockMode) -> Result<(u64, Vec<Event>), DlmError> {
+        let held = self.locks.get(&lock).ok_or(DlmError::NotFound)?;
+        if held.owner != owner {
+            return Err(DlmError::NotOwner);
+        }
+        let fence = match held.state {
+            State::Granted { fence } => fence,
+            State::Waiting { .. } => return Err(DlmError::Invalid),
+        };
+        if held.mode == mode {
+            return Ok((fence, Vec::new()));
+        }
+
+        let (namespace, current) = (held.namespace, held.mode);
+        let resource = self.namespaces[namespace as usize - 1].resources.get_mut(&held.resource).unwrap();
+        let mut events = Vec::new();
+        match current {
+            LockMode::Exclusive => {
+                self.locks.get_mut(&lock).unwrap().mode = LockMode::Shared;
+                promote(resource, &mut self.locks, &mut self.next_fence, &mut events);
+                Ok((fence, events))
+            }
+            LockMode::Shared => {
+                if resource.granted.len() != 1 || !resource.waiting.is_empty() {
+                    return Err(DlmError::WouldBlock);
+                }
+                let fence = self.next_fence;
+                self.next_fence += 1;
+                let held = self.locks.get_mut(&lock).unwrap();
+                held.mode = LockMode::Exclusive;
+                held.state = State::Granted { fence };
+                Ok((fence, events))
+            }
+        }
+    }
+
+    /// Extend a node's lease. Returns whether the previous lease was lost,
+    /// in which case the node no longer holds any lock.
+    pub fn renew(&mut self, node: u64, now_ns: u64) -> (bool, Vec<Event>) {
+        let mut events = Vec::new();
+        let restarted = match self.leases.get(&node) {
+            Some(lease) if lease.expiry_ns <= now_ns && !lease.lost => {
+                events = self.node_down(node);
+                true
+            }
+            Some(lease) => lease.lost,
+            None => false,
+        };
+        self.leases.insert(node, NodeLease { expiry_ns: now_ns + self.lease_ns, lost: false });
+        (restarted, events)
+    }
+
+    /// Release everything a failed node held and fail its queued requests
+    pub fn node_down(&mut self, node: u64) -> Vec<Event> {
+        if let Some(lease) = self.leases.get_mut(&node) {
+            lease.lost = true;
+        }
+        let ids: Vec<u64> = self.locks.iter().filter(|(_, lock)| lock.node == node).map(|(&id, _)| id).collect();
+        self.drop_locks(&ids, DlmError::LeaseLost)
+    }
+
+    /// Release everything an exited process held
+    pub fn release_owner(&mut self, owner: u64) -> Vec<Event> {
+        let ids: Vec<u64> = self.locks.iter().filter(|(_, lock)| lock.owner == owner).map(|(&id, _)| id).collect();
+        let mut events = self.drop_locks(&ids, DlmError::NotFound);
+        // Nobody is left to hear about the exited owner's own requests
+        events.retain(|event| !matches!(event, Event::Failed { owner: failed, .. } if *failed == owner));
+        events
+    }
+
+    fn drop_locks(&mut self, ids: &[u64], error: DlmError) -> Vec<Event> {
+        let mut events = Vec::new();
+        for &id in ids {
+            // Earlier removals may have granted this one already
+            if let Some(lock) = self.remove(id, &mut events) {
+                if matches!(lock.state, State::Waiting { .. }) {
+                    events.push(Event::Failed { owner: lock.owner, lock: id, error });
+                }
+            }
+        }
+        // Requests granted then dropped in the same pass were never held
+        events.retain(|event| match event {
+            Event::Granted { lock, .. } => self.locks.contains_key(lock),
+            Event::Failed { .. } => true,
+        });
+        events
+    }
+
+    /// Fail nodes whose lease lapsed and queued requests past their deadline
+    pub fn expire(&mut self, now_ns: u64) -> Vec<Event> {
+        let lapsed: Vec<u64> = self
+            .leases
+            .iter()
+            .filter(|(_, lease)| !lease.lost && lease.expiry_ns <= now_ns)
+            .map(|(&node, _)| node)
+            .collect();
+        let mut events = Vec::new();
+        for node in lapsed {
+            events.extend(self.node_down(node));
+        }
+
+        let overdue: Vec<u64> = self
+            .locks
+            .iter()
+            .filter(
+                |(_, lock)| matches!(lock.state, State::Waiting { deadline_ns: Some(deadline) } if deadline <= now_ns),
+            )
+            .map(|(&id, _)| id)
+            .collect();
+        events.extend(self.drop_locks(&overdue, DlmError::TimedOut));
+        events
+    }
+
+    /// Current holders of a resource, in grant order
+    pub fn holders(&mut self, namespace: u32, resource: &[u8]) -> Result<Vec<Holder>, DlmError> {
+        let namespace = self.namespace_mut(namespace)?;
+        let granted = match namespace.resources.get(resource) {
+            Some(resource) => resource.granted.clone(),
+            None => return Ok(Vec::new()),
+        };
+        Ok(granted
+            .iter()
+            .map(|id| {
+                let lock = &self.locks[id];
+                let fence = match lock.state {
+                    State::Granted { fence } => fence,
+                    State::Waiting { .. } => 0,
+                };
+                Holder { node: lock.node, mode: lock.mode, fence }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn granted(acquired: Result<Acquired, DlmError>) -> (u64, u64) {
+        match acquired {
+            Ok(Acquired::Granted { lock, fence }) => (lock, fence),
+            other => panic!("expected a grant, got {:?}", other),
+        }
+    }
+
+    fn queued(acquired: Result<Acquired, DlmError>) -> u64 {
+        match acquired {
+            Ok(Acquired::Queued { lock }) => lock,
+            other => panic!("expected a queued request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_modes_and_fifo_order() {
+        let mut dlm = LockManager::new(10 * SECOND);
+        let pool = dlm.open_namespace("pool").unwrap();
+        assert_eq!(dlm.open_namespace("pool"), Ok(pool));
+        assert_eq!(dlm.lock(1, pool + 1, b"vol", 1, LockMode::Shared, false, None, 0), Err(DlmError::NotFound));
+
+        let (a, fence_a) = granted(dlm.lock(1, pool, b"vol", 1, LockMode::Shared, false, None, 0));
+        let (_, fence_b) = granted(dlm.lock(2, pool, b"vol", 2, LockMode::Shared, false, None, 0));
+        assert!(fence_b > fence_a);
+        assert_eq!(dlm.lock(3, pool, b"vol", 3, LockMode::Exclusive, false, None, 0), Err(DlmError::WouldBlock));
+
+        // A queued writer holds back later readers
+        let writer = queued(dlm.lock(3, pool, b"vol", 3, LockMode::Exclusive, true, None, 0));
+        let reader = queued(dlm.lock(4, pool, b"vol", 4, LockMode::Shared, true, None, 0));
+        assert_eq!(dlm.unlock(2, a), Err(DlmError::NotOwner));
+        assert_eq!(dlm.unlock(1, a), Ok(vec![]));
+        let events = dlm.unlock(2, 2).unwrap();
+        assert!(matches!(events[..], [Event::Granted { owner: 3, lock, .. }] if lock == writer));
+        assert_eq!(dlm.holders(pool, b"vol").unwrap()[0].mode, LockMode::Exclusive);
+
+        let events = dlm.unlock(3, writer).unwrap();
+        assert!(matches!(events[..], [Event::Granted { owner: 4, lock, .. }] if lock == reader));
+        dlm.unlock(4, reader).unwrap();
+        assert_eq!(dlm.holders(pool, b"vol"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_conversions_never_wait() {
+        let mut dlm = LockManager::new(10 * SECOND);
+        let pool = dlm.open_namespace("pool").unwrap();
+        let (a, fence) = granted(dlm.lock(1, pool, b"vol", 1, LockMode::Shared, false, None, 0));
+        let (upgraded, _) = dlm.convert(1, a, LockMode::Exclusive).unwrap();
+        assert!(upgraded > fence);
+
+        let waiting = queued(dlm.lock(2, pool, b"vol", 2, LockMode::Shared, true, None, 0));
+        let (kept, events) = dlm.convert(1, a, LockMode::Shared).unwrap();
+        assert_eq!(kept, upgraded);
+        assert!(matches!(events[..], [Event::Granted { lock, .. }] if lock == waiting));
+        assert_eq!(dlm.convert(1, a, LockMode::Exclusive), Err(DlmError::WouldBlock));
+    }
+
+    #[test]
+    fn test_lapsed_leases_release_locks() {
+        let mut dlm = LockManager::new(10 * SECOND);
+        let pool = dlm.open_namespace("pool").unwrap();
+        let (_, old_fence) = granted(dlm.lock(1, pool, b"vol", 1, LockMode::Exclusive, false, None, 0));
+        let waiting = queued(dlm.lock(2, pool, b"vol", 2, LockMode::Exclusive, true, None, SECOND));
+
+        // Node 2 keeps renewing, node 1 goes silent
+        dlm.renew(2, 8 * SECOND);
+        assert!(dlm.expire(9 * SECOND).is_empty());
+        let events = dlm.expire(10 * SECOND);
+        let fence = match events[..] {
+            [Event::Granted { owner: 2, lock, fence }] if lock == waiting => fence,
+            _ => panic!("unexpected events {:?}", events),
+        };
+        assert!(fence > old_fence);
+
+        // The failed node has to renew before locking again, and learns its
+        // locks are gone
+        assert_eq!(
+            dlm.lock(1, pool, b"other", 1, LockMode::Shared, false, None, 11 * SECOND),
+            Err(DlmError::LeaseLost)
+        );
+        assert_eq!(dlm.renew(1, 11 * SECOND), (true, vec![]));
+        assert_eq!(dlm.renew(1, 12 * SECOND), (false, vec![]));
+        granted(dlm.lock(1, pool, b"other", 1, LockMode::Shared, false, None, 12 * SECOND));
+    }
+
+    #[test]
+    fn test_failures_end_queued_requests() {
+        let mut dlm = LockManager::new(10 * SECOND);
+        let pool = dlm.open_namespace("pool").unwrap();
+        granted(dlm.lock(1, pool, b"vol", 1, LockMode::Exclusive, false, None, 0));
+        let timed = queued(dlm.lock(2, pool, b"vol", 2, LockMode::Shared, true, Some(SECOND), 0));
+        let stranded = queued(dlm.lock(3, pool, b"vol", 3, LockMode::Shared, true, None, 0));
+
+        let events = dlm.expire(SECOND);
+        assert_eq!(events, vec![Event::Failed { owner: 2, lock: timed, error: DlmError::TimedOut }]);
+        let events = dlm.node_down(3);
+        assert_eq!(events, vec![Event::Failed { owner: 3, lock: stranded, error: DlmError::LeaseLost }]);
+
+        // An exiting process frees what it held
+        let next = queued(dlm.lock(4, pool, b"vol", 4, LockMode::Shared, true, None, 0));
+        let events = dlm.release_owner(1);
+        assert!(matches!(events[..], [Event::Granted { owner: 4, lock, .. }] if lock == next));
+    }
+}
@@ -0,0 +1,267 @@
+/*
+ * Orion Operating System - Lock Manager Protocol
+ *
+ * Wire format of the requests understood by the dlm server. All fields are
+ * little-endian; every message starts with a 32-bit opcode and every reply
+ * starts with a 32-bit signed status (0 or a negative errno). A LOCK that
+ * has to wait gets its reply only once it is granted, times out or its
+ * node's lease is lost.
+ *
+ *   OPEN_NAMESPACE  name                               -> namespace:u32
+ *   LOCK            namespace:u32 node:u64 mode:u8 flags:u8 reserved:u16
+ *                   timeout_ms:u32 resource            -> lock:u64 fence:u64
+ *   UNLOCK          lock:u64                           ->
+ *   CONVERT         lock:u64 mode:u8                   -> fence:u64
+ *   RENEW           node:u64                           -> lease_ms:u32 restarted:u32
+ *   QUERY           namespace:u32 resource             -> count:u32 {node:u64 fence:u64 mode:u32}
+ *   NODE_DOWN       node:u64                           ->
+ *   PROCESS_EXIT    pid:u64                            ->
+ *
+ * A timeout of 0 waits until granted. NODE_DOWN comes from the cluster
+ * membership layer, PROCESS_EXIT from the kernel.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::lock::{DlmError, Holder, LockMode};
+
+// Opcodes
+pub const OP_OPEN_NAMESPACE: u32 = 1;
+pub const OP_LOCK: u32 = 2;
+pub const OP_UNLOCK: u32 = 3;
+pub const OP_CONVERT: u32 = 4;
+pub const OP_RENEW: u32 = 5;
+pub const OP_QUERY: u32 = 6;
+pub const OP_NODE_DOWN: u32 = 7;
+pub const OP_PROCESS_EXIT: u32 = 8;
+
+// LOCK flags
+pub const LOCK_NOWAIT: u8 = 1 << 0;
+
+pub const MODE_SHARED: u8 = 0;
+pub const MODE_EXCLUSIVE: u8 = 1;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EACCES: i32 = -13;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+pub const STATUS_ETIMEDOUT: i32 = -110;
+pub const STATUS_ESTALE: i32 = -116;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DlmRequest {
+    OpenNamespace { name: String },
+    Lock { namespace: u32, node: u64, mode: LockMode, nowait: bool, timeout_ms: u32, resource: Vec<u8> },
+    Unlock { lock: u64 },
+    Convert { lock: u64, mode: LockMode },
+    Renew { node: u64 },
+    Query { namespace: u32, resource: Vec<u8> },
+    NodeDown { node: u64 },
+    ProcessExit { pid: u64 },
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+fn decode_mode(raw: u8) -> Option<LockMode> {
+    match raw {
+        MODE_SHARED => Some(LockMode::Shared),
+        MODE_EXCLUSIVE => Some(LockMode::Exclusive),
+        _ => None,
+    }
+}
+
+fn encode_mode(mode: LockMode) -> u8 {
+    match mode {
+        LockMode::Shared => MODE_SHARED,
+        LockMode::Exclusive => MODE_EXCLUSIVE,
+    }
+}
+
+impl DlmRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_OPEN_NAMESPACE => {
+                let name = core::str::from_utf8(data.get(4..)?).ok()?;
+                Some(DlmRequest::OpenNamespace { name: String::from(name) })
+            }
+            OP_LOCK => Some(DlmRequest::Lock {
+                namespace: read_u32(data, 4)?,
+                node: read_u64(data, 8)?,
+                mode: decode_mode(*data.get(16)?)?,
+                nowait: data.get(17)? & LOCK_NOWAIT != 0,
+                timeout_ms: read_u32(data, 20)?,
+                resource: data.get(24..)?.to_vec(),
+            }),
+            OP_UNLOCK => Some(DlmRequest::Unlock { lock: read_u64(data, 4)? }),
+            OP_CONVERT => Some(DlmRequest::Convert { lock: read_u64(data, 4)?, mode: decode_mode(*data.get(12)?)? }),
+            OP_RENEW => Some(DlmRequest::Renew { node: read_u64(data, 4)? }),
+            OP_QUERY => Some(DlmRequest::Query { namespace: read_u32(data, 4)?, resource: data.get(8..)?.to_vec() }),
+            OP_NODE_DOWN => Some(DlmRequest::NodeDown { node: read_u64(data, 4)? }),
+            OP_PROCESS_EXIT => Some(DlmRequest::ProcessExit { pid: read_u64(data, 4)? }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            DlmRequest::OpenNamespace { name } => {
+                out.extend_from_slice(&OP_OPEN_NAMESPACE.to_le_bytes());
+                out.extend_from_slice(name.as_bytes());
+            }
+            DlmRequest::Lock { namespace, node, mode, nowait, timeout_ms, resource } => {
+                out.extend_from_slice(&OP_LOCK.to_le_bytes());
+                out.extend_from_slice(&namespace.to_le_bytes());
+                out.extend_from_slice(&node.to_le_bytes());
+                out.push(encode_mode(*mode));
+                out.push(if *nowait { LOCK_NOWAIT } else { 0 });
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&timeout_ms.to_le_bytes());
+                out.extend_from_slice(resource);
+            }
+            DlmRequest::Unlock { lock } => {
+                out.extend_from_slice(&OP_UNLOCK.to_le_bytes());
+                out.extend_from_slice(&lock.to_le_bytes());
+            }
+            DlmRequest::Convert { lock, mode } => {
+                out.extend_from_slice(&OP_CONVERT.to_le_bytes());
+                out.extend_from_slice(&lock.to_le_bytes());
+                out.push(encode_mode(*mode));
+            }
+            DlmRequest::Renew { node } => {
+                out.extend_from_slice(&OP_RENEW.to_le_bytes());
+                out.extend_from_slice(&node.to_le_bytes());
+            }
+            DlmRequest::Query { namespace, resource } => {
+                out.extend_from_slice(&OP_QUERY.to_le_bytes());
+                out.extend_from_slice(&namespace.to_le_bytes());
+                out.extend_from_slice(resource);
+            }
+            DlmRequest::NodeDown { node } => {
+                out.extend_from_slice(&OP_NODE_DOWN.to_le_bytes());
+                out.extend_from_slice(&node.to_le_bytes());
+            }
+            DlmRequest::ProcessExit { pid } => {
+                out.extend_from_slice(&OP_PROCESS_EXIT.to_le_bytes());
+                out.extend_from_slice(&pid.to_le_bytes());
+            }
+        }
+        out
+    }
+}
+
+pub fn error_status(error: DlmError) -> i32 {
+    match error {
+        DlmError::NotFound => STATUS_ENOENT,
+        DlmError::WouldBlock => STATUS_EAGAIN,
+        DlmError::NotOwner => STATUS_EPERM,
+        DlmError::LeaseLost => STATUS_ESTALE,
+        DlmError::TimedOut => STATUS_ETIMEDOUT,
+        DlmError::Invalid => STATUS_EINVAL,
+        DlmError::NoSpace => STATUS_ENOSPC,
+        DlmError::Unavailable => STATUS_EIO,
+    }
+}
+
+pub fn status_error(status: i32) -> DlmError {
+    match status {
+        STATUS_ENOENT => DlmError::NotFound,
+        STATUS_EAGAIN => DlmError::WouldBlock,
+        STATUS_EPERM | STATUS_EACCES => DlmError::NotOwner,
+        STATUS_ESTALE => DlmError::LeaseLost,
+        STATUS_ETIMEDOUT => DlmError::TimedOut,
+        STATUS_EINVAL => DlmError::Invalid,
+        STATUS_ENOSPC => DlmError::NoSpace,
+        _ => DlmError::Unavailable,
+    }
+}
+
+/// QUERY payload: holder count, then node, fence and mode of each
+pub fn encode_holders(holders: &[Holder], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(holders.len() as u32).to_le_bytes());
+    for holder in holders {
+        out.extend_from_slice(&holder.node.to_le_bytes());
+        out.extend_from_slice(&holder.fence.to_le_bytes());
+        out.extend_from_slice(&(encode_mode(holder.mode) as u32).to_le_bytes());
+    }
+}
+
+pub fn decode_holders(payload: &[u8]) -> Option<Vec<Holder>> {
+    let count = read_u32(payload, 0)? as usize;
+    (0..count)
+        .map(|index| {
+            let offset = 4 + index * 20;
+            Some(Holder {
+                node: read_u64(payload, offset)?,
+                fence: read_u64(payload, offset + 8)?,
+                mode: decode_mode(read_u32(payload, offset + 16)? as u8)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_requests_round_trip() {
+        let requests = [
+            DlmRequest::OpenNamespace { name: String::from("pool0") },
+            DlmRequest::Lock {
+                namespace: 1,
+                node: 7,
+                mode: LockMode::Exclusive,
+                nowait: true,
+                timeout_ms: 250,
+                resource: b"volume/3".to_vec(),
+            },
+            DlmRequest::Convert { lock: 9, mode: LockMode::Shared },
+            DlmRequest::Query { namespace: 1, resource: b"volume/3".to_vec() },
+            DlmRequest::NodeDown { node: 7 },
+        ];
+        for request in requests {
+            assert_eq!(DlmRequest::decode(&request.encode()), Some(request));
+        }
+
+        let mut bad_mode = DlmRequest::Convert { lock: 9, mode: LockMode::Shared }.encode();
+        bad_mode[12] = 2;
+        assert_eq!(DlmRequest::decode(&bad_mode), None);
+        assert_eq!(DlmRequest::decode(&OP_UNLOCK.to_le_bytes()), None);
+    }
+
+    #[test]
+    fn test_holders_round_trip() {
+        let holders = vec![
+            Holder { node: 1, mode: LockMode::Shared, fence: 4 },
+            Holder { node: 2, mode: LockMode::Shared, fence: 5 },
+        ];
+        let mut payload = Vec::new();
+        encode_holders(&holders, &mut payload);
+        assert_eq!(decode_holders(&payload), Some(holders));
+        assert_eq!(decode_holders(&payload[..payload.len() - 1]), None);
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * Orion Operating System - Lock Manager Dispatch
+ *
+ * Turns decoded requests into lock table operations and replies. Replies
+ * are returned as (recipient, message) pairs rather than sent, since one
+ * request can settle other processes' queued LOCKs: an unlock may grant
+ * waiters, a NODE_DOWN may fail them. Callers check capabilities before
+ * dispatching; nothing here knows who may send NODE_DOWN or PROCESS_EXIT.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_proto::reply;
+
+use crate::lock::{Acquired, Event, LockManager};
+use crate::protocol::{encode_holders, error_status, DlmRequest, STATUS_OK};
+
+/// Reply messages to send, with their recipient
+pub type Outbox = Vec<(u64, Vec<u8>)>;
+
+fn lock_reply(lock: u64, fence: u64) -> Vec<u8> {
+    let mut payload = [0u8; 16];
+    payload[..8].copy_from_slice(&lock.to_le_bytes());
+    payload[8..].copy_from_slice(&fence.to_le_bytes());
+    reply(STATUS_OK, &payload)
+}
+
+/// Answer the LOCK requests settled by `events`
+fn post_events(events: Vec<Event>, outbox: &mut Outbox) {
+    for event in events {
+        match event {
+            Event::Granted { owner, lock, fence } => outbox.push((owner, lock_reply(lock, fence))),
+            Event::Failed { owner, error, .. } => outbox.push((owner, reply(error_status(error), &[]))),
+        }
+    }
+}
+
+/// Run one request from `sender`, queueing its reply unless it is a LOCK
+/// left waiting
+pub fn handle(manager: &mut LockManager, sender: u64, request: DlmRequest, now_ns: u64, outbox: &mut Outbox) {
+    let response = match request {
+        DlmRequest::OpenNamespace { name } => {
+            manager.open_namespace(&name).map(|namespace| reply(STATUS_OK, &namespace.to_le_bytes()))
+        }
+        DlmRequest::Lock { namespace, node, mode, nowait, timeout_ms, resource } => {
+            let deadline = (timeout_ms != 0).then(|| now_ns + timeout_ms as u64 * 1_000_000);
+            match manager.lock(sender, namespace, &resource, node, mode, !nowait, deadline, now_ns) {
+                Ok(Acquired::Granted { lock, fence }) => Ok(lock_reply(lock, fence)),
+                Ok(Acquired::Queued { .. }) => return,
+                Err(error) => Err(error),
+            }
+        }
+        DlmRequest::Unlock { lock } => manager.unlock(sender, lock).map(|events| {
+            post_events(events, outbox);
+            reply(STATUS_OK, &[])
+        }),
+        DlmRequest::Convert { lock, mode } => manager.convert(sender, lock, mode).map(|(fence, events)| {
+            post_events(events, outbox);
+            reply(STATUS_OK, &fence.to_le_bytes())
+        }),
+        DlmRequest::Renew { node } => {
+            let (restarted, events) = manager.renew(node, now_ns);
+            post_events(events, outbox);
+            let mut payload = [0u8; 8];
+            payload[..4].copy_from_slice(&((manager.lease_ns() / 1_000_000) as u32).to_le_bytes());
+            payload[4..].copy_from_slice(&(restarted as u32).to_le_bytes());
+            Ok(reply(STATUS_OK, &payload))
+        }
+        DlmRequest::Query { namespace, resource } => manager.holders(namespace, &resource).map(|holders| {
+            let mut payload = Vec::new();
+            encode_holders(&holders, &mut payload);
+            reply(STATUS_OK, &payload)
+        }),
+        DlmRequest::NodeDown { node } => {
+            post_events(manager.node_down(node), outbox);
+            Ok(reply(STATUS_OK, &[]))
+        }
+        DlmRequest::ProcessExit { pid } => {
+            post_events(manager.release_owner(pid), outbox);
+            return;
+        }
+    };
+    outbox.push((sender, response.unwrap_or_else(|error| reply(error_status(error), &[]))));
+}
+
+/// Periodic work: fail lapsed leases and overdue waiters
+pub fn tick(manager: &mut LockManager, now_ns: u64, outbox: &mut Outbox) {
+    post_events(manager.expire(now_ns), outbox);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::LockMode;
+    use crate::protocol::{read_u64, STATUS_ETIMEDOUT};
+    use alloc::vec;
+
+    fn lock(node: u64, mode: LockMode, timeout_ms: u32) -> DlmRequest {
+        DlmRequest::Lock { namespace: 1, node, mode, nowait: false, timeout_ms, resource: b"vol".to_vec() }
+    }
+
+    #[test]
+    fn test_waiters_are_answered_later() {
+        let mut manager = LockManager::new(10_000_000_000);
+        let mut outbox = Outbox::new();
+        manager.open_namespace("pool").unwrap();
+
+        handle(&mut manager, 10, lock(1, LockMode::Exclusive, 0), 0, &mut outbox);
+        let (recipient, granted) = outbox.pop().unwrap();
+        assert_eq!(recipient, 10);
+        let held = read_u64(&granted, 4).unwrap();
+
+        handle(&mut manager, 20, lock(2, LockMode::Shared, 0), 0, &mut outbox);
+        handle(&mut manager, 30, lock(3, LockMode::Shared, 5), 0, &mut outbox);
+        assert!(outbox.is_empty());
+
+        // The bounded waiter gives up, the other is granted on unlock
+        tick(&mut manager, 5_000_000, &mut outbox);
+        assert_eq!(outbox, vec![(30, reply(STATUS_ETIMEDOUT, &[]))]);
+        outbox.clear();
+        handle(&mut manager, 10, DlmRequest::Unlock { lock: held }, 0, &mut outbox);
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox[0].0, 20);
+        assert_eq!(outbox[1], (10, reply(STATUS_OK, &[])));
+    }
+}
@@ -0,0 +1,105 @@
+/*
+ * Orion Operating System - Download Checksums
+ *
+ * Expected digests of downloaded data, written `sha256:<hex>` or
+ * `sha512:<hex>` as package indexes and firmware manifests publish them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use orion_crypto::sha256::{Sha256, SHA256_DIGEST_SIZE};
+use orion_crypto::sha512::{Sha512, SHA512_DIGEST_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    Sha256([u8; SHA256_DIGEST_SIZE]),
+    Sha512([u8; SHA512_DIGEST_SIZE]),
+}
+
+fn decode_hex(text: &str, out: &mut [u8]) -> Option<()> {
+    if text.len() != out.len() * 2 {
+        return None;
+    }
+    for (byte, pair) in out.iter_mut().zip(text.as_bytes().chunks_exact(2)) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(())
+}
+
+impl Checksum {
+    /// Parse `sha256:<hex>` or `sha512:<hex>`
+    pub fn parse(text: &str) -> Option<Checksum> {
+        let (algorithm, hex) = text.trim().split_once(':')?;
+        if algorithm.eq_ignore_ascii_case("sha256") {
+            let mut digest = [0u8; SHA256_DIGEST_SIZE];
+            decode_hex(hex, &mut digest)?;
+            Some(Checksum::Sha256(digest))
+        } else if algorithm.eq_ignore_ascii_case("sha512") {
+            let mut digest = [0u8; SHA512_DIGEST_SIZE];
+            decode_hex(hex, &mut digest)?;
+            Some(Checksum::Sha512(digest))
+        } else {
+            None
+        }
+    }
+
+    /// A hasher of the algorithm this checksum uses
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            Checksum::Sha256(_) => Hasher::Sha256(Sha256::new()),
+            Checksum::Sha512(_) => Hasher::Sha512(Sha512::new()),
+        }
+    }
+}
+
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Whether the data hashed so far has the expected digest
+    pub fn matches(self, expected: &Checksum) -> bool {
+        match (self, expected) {
+            (Hasher::Sha256(hasher), Checksum::Sha256(digest)) => hasher.finalize() == *digest,
+            (Hasher::Sha512(hasher), Checksum::Sha512(digest)) => hasher.finalize() == *digest,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_and_checks() {
+        let checksum =
+            Checksum::parse("SHA256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").unwrap();
+        let mut hasher = checksum.hasher();
+        hasher.update(b"ab");
+        hasher.update(b"c");
+        assert!(hasher.matches(&checksum));
+
+        let mut hasher = checksum.hasher();
+        hasher.update(b"abd");
+        assert!(!hasher.matches(&checksum));
+
+        let sha512 = Checksum::parse(&alloc::format!("sha512:{}", "00".repeat(64))).unwrap();
+        assert!(!sha512.hasher().matches(&sha512));
+        assert_eq!(Checksum::parse("sha256:abcd"), None);
+        assert_eq!(Checksum::parse("md5:d41d8cd98f00b204e9800998ecf8427e"), None);
+    }
+}
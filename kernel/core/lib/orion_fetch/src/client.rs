@@ -0,0 +1,517 @@
+/*
+ * Orion Operating System - Fetch Client
+ *
+ * One GET per connection (`Connection: close`), identity encoding, so a
+ * byte count of the stored body is also its offset in the resource.
+ * Redirects are followed up to a limit, never from https to http. A
+ * download resumes with a Range request after the connection drops:
+ * a 206 must continue exactly where the stored data ends, a 200 means
+ * the server ignored the range and the download starts over, and a 416
+ * for the stored length means nothing was missing. Checksums cover the
+ * whole stored body, the part kept from earlier attempts included.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_http::{IoError, Transport};
+
+use crate::checksum::Checksum;
+use crate::connector::Connector;
+use crate::response::{BodyDecoder, Head};
+use crate::url::{Scheme, Url};
+use crate::FetchError;
+
+/// Largest response head accepted
+const MAX_HEAD: usize = 16 * 1024;
+
+const READ_CHUNK: usize = 8192;
+
+/// Where downloaded data goes; a file for the tools, a buffer for tests
+/// and small documents
+pub trait Sink {
+    /// Bytes stored so far, where a resumed download continues
+    fn stored(&self) -> u64;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), FetchError>;
+
+    /// Drop everything stored, the server is sending the whole body again
+    fn reset(&mut self) -> Result<(), FetchError>;
+
+    /// Hand the stored bytes to `consumer` in order
+    fn replay(&mut self, consumer: &mut dyn FnMut(&[u8])) -> Result<(), FetchError>;
+}
+
+impl Sink for Vec<u8> {
+    fn stored(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), FetchError> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), FetchError> {
+        self.clear();
+        Ok(())
+    }
+
+    fn replay(&mut self, consumer: &mut dyn FnMut(&[u8])) -> Result<(), FetchError> {
+        consumer(self);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    pub max_redirects: u32,
+    /// Attempts of a download in a row that store nothing before it fails
+    pub max_attempts: u32,
+    /// Consecutive idle polls of a connection before it times out
+    pub idle_limit: u32,
+    pub user_agent: String,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self { max_redirects: 5, max_attempts: 3, idle_limit: 30_000, user_agent: String::from("orion-fetch/1.0") }
+    }
+}
+
+/// Outcome of a download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fetched {
+    /// URL the data finally came from, after redirects
+    pub url: Url,
+    /// Length of the stored body
+    pub length: u64,
+    /// Bytes that were already stored when the download started
+    pub resumed_from: u64,
+}
+
+/// Requested byte range, `end` inclusive
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    start: u64,
+    end: Option<u64>,
+}
+
+/// A response whose head has been read, with the body bytes that came
+/// along with it
+struct Exchange<S: Transport> {
+    stream: S,
+    head: Head,
+    pending: Vec<u8>,
+}
+
+impl<S: Transport> Drop for Exchange<S> {
+    fn drop(&mut self) {
+        self.stream.close();
+    }
+}
+
+pub struct Client<C: Connector> {
+    connector: C,
+    config: FetchConfig,
+}
+
+impl<C: Connector> Client<C> {
+    pub fn new(connector: C, config: FetchConfig) -> Self {
+        Self { connector, config }
+    }
+
+    /// Whole body of `url`
+    pub fn get(&mut self, url: &str) -> Result<Vec<u8>, FetchError> {
+        let mut body = Vec::new();
+        self.download(url, &mut body, None)?;
+        Ok(body)
+    }
+
+    /// Bytes `start..=end` of `url` (`start..` without an end)
+    pub fn get_range(&mut self, url: &str, start: u64, end: Option<u64>) -> Result<Vec<u8>, FetchError> {
+        let (_, mut exchange) = self.open(&Url::parse(url)?, Some(Range { start, end }))?;
+        match exchange.head.status {
+            206 if exchange.head.content_range().is_some_and(|range| range.start == start) => {}
+            206 => return Err(FetchError::Malformed),
+            200 => return Err(FetchError::RangeIgnored),
+            status => return Err(FetchError::Status(status)),
+        }
+        let mut body = Vec::new();
+        self.read_body(&mut exchange, &mut |data| {
+            body.extend_from_slice(data);
+            Ok(())
+        })?;
+        Ok(body)
+    }
+
+    /// Download `url` into `sink`, continuing after what it already holds,
+    /// and check the result against `checksum`
+    pub fn download(
+        &mut self,
+        url: &str,
+        sink: &mut dyn Sink,
+        checksum: Option<&Checksum>,
+    ) -> Result<Fetched, FetchError> {
+        let url = Url::parse(url)?;
+        let resumed_from = sink.stored();
+        let mut failures = 0;
+        let final_url = loop {
+            let before = sink.stored();
+            match self.download_once(&url, sink) {
+                Ok(final_url) => break final_url,
+                // Retry transfers that broke off; the stored data stays
+                Err(error @ (FetchError::Connect | FetchError::Closed | FetchError::Timeout)) => {
+                    failures = if sink.stored() > before { 1 } else { failures + 1 };
+                    if failures >= self.config.max_attempts {
+                        return Err(error);
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        };
+
+        if let Some(checksum) = checksum {
+            let mut hasher = checksum.hasher();
+            sink.replay(&mut |data| hasher.update(data))?;
+            if !hasher.matches(checksum) {
+                return Err(FetchError::ChecksumMismatch);
+            }
+        }
+        Ok(Fetched { url: final_url, length: sink.stored(), resumed_from })
+    }
+
+    fn download_once(&mut self, url: &Url, sink: &mut dyn Sink) -> Result<Url, FetchError> {
+        let offset = sink.stored();
+        let range = if offset > 0 { Some(Range { start: offset, end: None }) } else { None };
+        let (final_url, mut exchange) = self.open(url, range)?;
+        match exchange.head.status {
+            200 if offset > 0 => sink.reset()?,
+            200 => {}
+            206 if offset > 0 && exchange.head.content_range().is_some_and(|range| range.start == offset) => {}
+            206 => return Err(FetchError::Malformed),
+            // Everything was stored before the connection dropped
+            416 if offset > 0 && exchange.head.unsatisfied_length() == Some(offset) => return Ok(final_url),
+            status => return Err(FetchError::Status(status)),
+        }
+        self.read_body(&mut exchange, &mut |data| sink.write(data))?;
+        Ok(final_url)
+    }
+
+    /// Send the request and follow redirects to the final response head
+    fn open(&mut self, url: &Url, range: Option<Range>) -> Result<(Url, Exchange<C::Stream>), FetchError> {
+        let mut url = url.clone();
+        let mut redirects = 0;
+        loop {
+            let exchange = self.exchange(&url, range)?;
+            if !exchange.head.is_redirect() {
+                return Ok((url, exchange));
+            }
+            let location = exchange.head.header("location").ok_or(FetchError::Malformed)?;
+            let next = url.join(location)?;
+            if url.scheme == Scheme::Https && next.scheme == Scheme::Http {
+                return Err(FetchError::InsecureRedirect);
+            }
+            redirects += 1;
+            if redirects > self.config.max_redirects {
+                return Err(FetchError::TooManyRedirects);
+            }
+            url = next;
+        }
+    }
+
+    /// One request on a new connection, up to the final response head
+    fn exchange(&mut self, url: &Url, range: Option<Range>) -> Result<Exchange<C::Stream>, FetchError> {
+        let mut request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\nAccept-Encoding: identity\r\nConnection: close\r\n",
+            url.path,
+            url.authority(),
+            self.config.user_agent
+        );
+        if let Some(range) = range {
+            match range.end {
+                Some(end) => request.push_str(&format!("Range: bytes={}-{}\r\n", range.start, end)),
+                None => request.push_str(&format!("Range: bytes={}-\r\n", range.start)),
+            }
+        }
+        request.push_str("\r\n");
+
+        let mut stream = self.connector.connect(url)?;
+        let result = self.send(&mut stream, request.as_bytes()).and_then(|_| self.receive_head(&mut stream));
+        match result {
+            Ok((head, pending)) => Ok(Exchange { stream, head, pending }),
+            Err(error) => {
+                stream.close();
+                Err(error)
+            }
+        }
+    }
+
+    fn send(&mut self, stream: &mut C::Stream, mut data: &[u8]) -> Result<(), FetchError> {
+        let mut idle = 0;
+        while !data.is_empty() {
+            match stream.write(data) {
+                Ok(sent) => {
+                    data = &data[sent.min(data.len())..];
+                    idle = 0;
+                }
+                Err(IoError::WouldBlock) => self.idle(&mut idle)?,
+                Err(IoError::Closed) => return Err(FetchError::Closed),
+            }
+        }
+        Ok(())
+    }
+
+    /// Next bytes from the stream, None once the server closed it
+    fn receive(&mut self, stream: &mut C::Stream, buffer: &mut [u8]) -> Result<Option<usize>, FetchError> {
+        let mut idle = 0;
+        loop {
+            match stream.read(buffer) {
+                Ok(0) | Err(IoError::WouldBlock) => self.idle(&mut idle)?,
+                Ok(count) => return Ok(Some(count)),
+                Err(IoError::Closed) => return Ok(None),
+            }
+        }
+    }
+
+    /// Read up to the final head; interim 1xx heads are skipped
+    fn receive_head(&mut self, stream: &mut C::Stream) -> Result<(Head, Vec<u8>), FetchError> {
+        let mut data = Vec::new();
+        let mut buffer = [0u8; READ_CHUNK];
+        loop {
+            while let Some((head, length)) = Head::parse(&data)? {
+                data.drain(..length);
+                if head.status >= 200 {
+                    return Ok((head, data));
+                }
+            }
+            if data.len() > MAX_HEAD {
+                return Err(FetchError::Malformed);
+            }
+            match self.receive(stream, &mut buffer)? {
+                Some(count) => data.extend_from_slice(&buffer[..count]),
+                None => return Err(FetchError::Closed),
+            }
+        }
+    }
+
+    fn read_body(
+        &mut self,
+        exchange: &mut Exchange<C::Stream>,
+        out: &mut dyn FnMut(&[u8]) -> Result<(), FetchError>,
+    ) -> Result<(), FetchError> {
+        let mut decoder = BodyDecoder::new(exchange.head.framing()?);
+        let pending = core::mem::take(&mut exchange.pending);
+        if decoder.is_done() || decoder.feed(&pending, out)? {
+            return Ok(());
+        }
+        let mut buffer = [0u8; READ_CHUNK];
+        loop {
+            match self.receive(&mut exchange.stream, &mut buffer)? {
+                Some(count) => {
+                    if decoder.feed(&buffer[..count], out)? {
+                        return Ok(());
+                    }
+                }
+                None => return decoder.finish_on_close(),
+            }
+        }
+    }
+
+    fn idle(&mut self, idle: &mut u32) -> Result<(), FetchError> {
+        *idle += 1;
+        if *idle > self.config.idle_limit {
+            return Err(FetchError::Timeout);
+        }
+        self.connector.wait();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::rc::Rc;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use core::cell::RefCell;
+
+    /// Replays one scripted response per connection and records requests
+    struct MockStream {
+        response: Vec<u8>,
+        position: usize,
+        /// Drop the connection after this many response bytes
+        cut_at: Option<usize>,
+        requests: Rc<RefCell<Vec<String>>>,
+        request: Vec<u8>,
+    }
+
+    impl Transport for MockStream {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+            let end = self.cut_at.unwrap_or(self.response.len()).min(self.response.len());
+            if self.position >= end {
+                return Err(IoError::Closed);
+            }
+            // Short reads exercise the incremental parsers
+            let count = buffer.len().min(end - self.position).min(7);
+            buffer[..count].copy_from_slice(&self.response[self.position..self.position + count]);
+            self.position += count;
+            Ok(count)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+            self.request.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn close(&mut self) {
+            if !self.request.is_empty() {
+                let request = core::mem::take(&mut self.request);
+                self.requests.borrow_mut().push(String::from_utf8(request).unwrap());
+            }
+        }
+    }
+
+    struct MockConnector {
+        responses: VecDeque<(String, Vec<u8>, Option<usize>)>,
+        requests: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl MockConnector {
+        fn new(responses: &[(&str, &[u8], Option<usize>)]) -> Self {
+            Self {
+                responses: responses.iter().map(|(url, data, cut)| (url.to_string(), data.to_vec(), *cut)).collect(),
+                requests: Rc::new(RefCell::new(Vec::new())),
+            }
+        }
+    }
+
+    impl Connector for MockConnector {
+        type Stream = MockStream;
+
+        fn connect(&mut self, url: &Url) -> Result<MockStream, FetchError> {
+            let (expected, response, cut_at) = self.responses.pop_front().ok_or(FetchError::Connect)?;
+            assert_eq!(url.to_string(), expected);
+            Ok(MockStream { response, position: 0, cut_at, requests: self.requests.clone(), request: Vec::new() })
+        }
+
+        fn wait(&mut self) {}
+    }
+
+    fn mock_client(responses: &[(&str, &[u8], Option<usize>)]) -> (Client<MockConnector>, Rc<RefCell<Vec<String>>>) {
+        let connector = MockConnector::new(responses);
+        let requests = connector.requests.clone();
+        (Client::new(connector, FetchConfig::default()), requests)
+    }
+
+    const SHA256_ABC: &str = "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn test_follows_redirects_and_decodes() {
+        let (mut client, requests) = mock_client(&[
+            (
+                "http://mirror/pkg",
+                b"HTTP/1.1 302 Found\r\nLocation: https://cdn:8443/pool/pkg\r\nContent-Length: 0\r\n\r\n",
+                None,
+            ),
+            (
+                "https://cdn:8443/pool/pkg",
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n1\r\nc\r\n0\r\n\r\n",
+                None,
+            ),
+        ]);
+        let mut body = Vec::new();
+        let fetched = client.download("http://mirror/pkg", &mut body, Checksum::parse(SHA256_ABC).as_ref()).unwrap();
+        assert_eq!(body, b"abc");
+        assert_eq!((fetched.url.to_string().as_str(), fetched.length), ("https://cdn:8443/pool/pkg", 3));
+        let requests = requests.borrow();
+        assert!(requests[0].starts_with("GET /pkg HTTP/1.1\r\nHost: mirror\r\n"));
+        assert!(requests[1].starts_with("GET /pool/pkg HTTP/1.1\r\nHost: cdn:8443\r\n"));
+        assert!(requests[1].contains("Connection: close\r\n") && !requests[1].contains("Range"));
+
+        let (mut client, _) =
+            mock_client(&[("https://mirror/pkg", b"HTTP/1.1 301 Moved\r\nLocation: http://mirror/pkg\r\n\r\n", None)]);
+        assert_eq!(client.get("https://mirror/pkg"), Err(FetchError::InsecureRedirect));
+
+        let loop_response: &[u8] = b"HTTP/1.1 307 Again\r\nLocation: /pkg\r\n\r\n";
+        let (mut client, _) = mock_client(&[("http://mirror/pkg", loop_response, None); 6]);
+        assert_eq!(client.get("http://mirror/pkg"), Err(FetchError::TooManyRedirects));
+
+        let (mut client, _) =
+            mock_client(&[("http://mirror/pkg", b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n", None)]);
+        assert_eq!(client.get("http://mirror/pkg"), Err(FetchError::Status(404)));
+    }
+
+    #[test]
+    fn test_resumes_interrupted_downloads() {
+        let full: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789";
+        let (mut client, requests) = mock_client(&[
+            ("http://mirror/img", full, Some(full.len() - 6)),
+            ("http://mirror/img", b"HTTP/1.1 206 Partial\r\nContent-Range: bytes 4-9/10\r\n\r\n456789", None),
+        ]);
+        let mut body = Vec::new();
+        let fetched = client.download("http://mirror/img", &mut body, None).unwrap();
+        assert_eq!((body.as_slice(), fetched.resumed_from), (&b"0123456789"[..], 0));
+        assert!(requests.borrow()[1].contains("Range: bytes=4-\r\n"));
+
+        // The server ignores the range: start over
+        let (mut client, _) = mock_client(&[("http://mirror/img", full, None)]);
+        let mut body = b"0123".to_vec();
+        let fetched = client.download("http://mirror/img", &mut body, None).unwrap();
+        assert_eq!((body.as_slice(), fetched.resumed_from), (&b"0123456789"[..], 4));
+
+        // A range starting elsewhere is refused
+        let (mut client, _) = mock_client(&[(
+            "http://mirror/img",
+            b"HTTP/1.1 206 Partial\r\nContent-Range: bytes 0-9/10\r\n\r\n0123456789",
+            None,
+        )]);
+        assert_eq!(client.download("http://mirror/img", &mut b"0123".to_vec(), None), Err(FetchError::Malformed));
+
+        // Already complete
+        let (mut client, _) = mock_client(&[(
+            "http://mirror/img",
+            b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */3\r\n\r\n",
+            None,
+        )]);
+        let mut body = b"abc".to_vec();
+        assert!(client.download("http://mirror/img", &mut body, Checksum::parse(SHA256_ABC).as_ref()).is_ok());
+        let mut body = b"abd".to_vec();
+        let (mut client, _) = mock_client(&[(
+            "http://mirror/img",
+            b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */3\r\n\r\n",
+            None,
+        )]);
+        assert_eq!(
+            client.download("http://mirror/img", &mut body, Checksum::parse(SHA256_ABC).as_ref()),
+            Err(FetchError::ChecksumMismatch)
+        );
+
+        // Attempts that store nothing give up
+        let (mut client, _) = mock_client(&[("http://mirror/img", full, Some(10)); 3]);
+        assert_eq!(client.download("http://mirror/img", &mut Vec::new(), None), Err(FetchError::Closed));
+    }
+
+    #[test]
+    fn test_fetches_ranges() {
+        let (mut client, requests) = mock_client(&[
+            (
+                "http://mirror/img",
+                b"HTTP/1.1 206 Partial\r\nContent-Range: bytes 2-4/10\r\nContent-Length: 3\r\n\r\n234",
+                None,
+            ),
+            ("http://mirror/img", b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789", None),
+        ]);
+        assert_eq!(client.get_range("http://mirror/img", 2, Some(4)), Ok(vec![b'2', b'3', b'4']));
+        assert!(requests.borrow()[0].contains("Range: bytes=2-4\r\n"));
+        assert_eq!(client.get_range("http://mirror/img", 2, Some(4)), Err(FetchError::RangeIgnored));
+    }
+}
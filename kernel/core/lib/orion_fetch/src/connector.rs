@@ -0,0 +1,97 @@
+/*
+ * Orion Operating System - Fetch Connections
+ *
+ * Where the client gets its connections from. NetConnector opens them
+ * through the network server's socket interface, with TLS run by the
+ * server and checked against a keyring-held CA certificate. There is no
+ * unicast DNS resolver yet, so host names need a resolver supplied by
+ * the caller; dotted IPv4 addresses always work.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use orion_http::socket::{NetChannel, NetStream};
+use orion_http::Transport;
+
+use crate::url::{Scheme, Url};
+use crate::FetchError;
+
+pub trait Connector {
+    type Stream: Transport;
+
+    /// Open a connection to the host and port of `url`, with TLS for https
+    fn connect(&mut self, url: &Url) -> Result<Self::Stream, FetchError>;
+
+    /// Called while a connection has nothing to transfer, before trying again
+    fn wait(&mut self);
+}
+
+/// Parse a dotted IPv4 address into the host-order value the network
+/// server expects
+pub fn parse_ipv4(text: &str) -> Option<u32> {
+    let mut octets = [0u8; 4];
+    let mut parts = text.split('.');
+    for octet in octets.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        *octet = part.parse().ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(u32::from_be_bytes(octets))
+}
+
+pub struct NetConnector<C: NetChannel, F: FnMut() -> Option<C>> {
+    /// Opens a fresh channel to the network server for each connection
+    channels: F,
+    /// Keyring handle of the CA certificate https servers must chain to
+    trust_anchor: Option<u64>,
+    resolve: fn(&str) -> Option<u32>,
+    wait: fn(),
+}
+
+impl<C: NetChannel, F: FnMut() -> Option<C>> NetConnector<C, F> {
+    /// `wait` sleeps briefly between polls of an idle connection
+    pub fn new(channels: F, wait: fn()) -> Self {
+        Self { channels, trust_anchor: None, resolve: parse_ipv4, wait }
+    }
+
+    pub fn with_trust_anchor(mut self, handle: u64) -> Self {
+        self.trust_anchor = Some(handle);
+        self
+    }
+
+    /// Resolve host names; the resolver sees dotted IPv4 addresses too
+    pub fn with_resolver(mut self, resolve: fn(&str) -> Option<u32>) -> Self {
+        self.resolve = resolve;
+        self
+    }
+}
+
+impl<C: NetChannel, F: FnMut() -> Option<C>> Connector for NetConnector<C, F> {
+    type Stream = NetStream<C>;
+
+    fn connect(&mut self, url: &Url) -> Result<NetStream<C>, FetchError> {
+        let ip = (self.resolve)(&url.host).ok_or(FetchError::UnresolvedHost)?;
+        let channel = (self.channels)().ok_or(FetchError::Connect)?;
+        match url.scheme {
+            Scheme::Http => NetStream::connect(channel, ip, url.port),
+            Scheme::Https => {
+                let anchor = self.trust_anchor.ok_or(FetchError::NoTrustAnchor)?;
+                NetStream::connect_tls(channel, ip, url.port, anchor, &url.host)
+            }
+        }
+        .map_err(|_| FetchError::Connect)
+    }
+
+    fn wait(&mut self) {
+        (self.wait)();
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * Orion Operating System - HTTP(S) Fetch
+ *
+ * Minimal HTTP/1.1 client for downloading packages and images: one GET
+ * per connection, redirects, ranged requests, downloads that resume
+ * where an interrupted transfer stopped and checksum validation of the
+ * result. TLS is run by the network server (CONNECT_TLS), which checks
+ * the server certificate against a keyring-held CA before any byte of
+ * the response reaches the client.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod checksum;
+pub mod client;
+pub mod connector;
+pub mod response;
+pub mod url;
+
+pub use checksum::{Checksum, Hasher};
+pub use client::{Client, FetchConfig, Fetched, Sink};
+pub use connector::{parse_ipv4, Connector, NetConnector};
+pub use response::{BodyDecoder, ContentRange, Framing, Head};
+pub use url::{Scheme, Url};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchError {
+    /// The URL could not be parsed
+    InvalidUrl,
+    /// Only http and https are fetched
+    UnsupportedScheme,
+    /// The host name has no address
+    UnresolvedHost,
+    /// An https URL without a trust anchor to check the server against
+    NoTrustAnchor,
+    /// The network server refused to open the connection
+    Connect,
+    /// The connection closed before the response was complete
+    Closed,
+    /// The server stopped sending for longer than the idle limit
+    Timeout,
+    /// The response is not valid HTTP/1.1
+    Malformed,
+    /// The server answered with an unexpected status
+    Status(u16),
+    /// A range was asked for and the server sent the whole body
+    RangeIgnored,
+    TooManyRedirects,
+    /// A redirect from https to http
+    InsecureRedirect,
+    /// The downloaded data does not match the expected digest
+    ChecksumMismatch,
+    /// Storing the downloaded data failed
+    Sink,
+}
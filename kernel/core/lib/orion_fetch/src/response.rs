@@ -0,0 +1,320 @@
+/*
+ * Orion Operating System - Fetch Responses
+ *
+ * Response heads (status line and header fields) and the body decoder
+ * for the three ways HTTP/1.1 frames a response body: Content-Length,
+ * chunked transfer coding, and everything until the server closes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::FetchError;
+
+/// Longest chunk-size or trailer line accepted
+const MAX_LINE: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    pub status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// Content-Range of a 206 response; `end` is inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    pub start: u64,
+    pub end: u64,
+    pub total: Option<u64>,
+}
+
+impl Head {
+    /// Parse the head at the start of `data`. Returns the head and the
+    /// number of bytes it took, or None while the blank line ending it has
+    /// not arrived yet
+    pub fn parse(data: &[u8]) -> Result<Option<(Head, usize)>, FetchError> {
+        let end = match data.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let text = core::str::from_utf8(&data[..end]).map_err(|_| FetchError::Malformed)?;
+        let mut lines = text.split("\r\n");
+
+        let status_line = lines.next().ok_or(FetchError::Malformed)?;
+        let mut fields = status_line.splitn(3, ' ');
+        if !fields.next().is_some_and(|version| version.starts_with("HTTP/1.")) {
+            return Err(FetchError::Malformed);
+        }
+        let status = match fields.next().map(str::parse::<u16>) {
+            Some(Ok(status)) if (100..600).contains(&status) => status,
+            _ => return Err(FetchError::Malformed),
+        };
+
+        let mut headers = Vec::new();
+        for line in lines {
+            // Obsolete line folding is rejected, as RFC 9112 allows
+            if line.starts_with([' ', '\t']) {
+                return Err(FetchError::Malformed);
+            }
+            let (name, value) = line.split_once(':').ok_or(FetchError::Malformed)?;
+            if name.is_empty() || name.ends_with([' ', '\t']) {
+                return Err(FetchError::Malformed);
+            }
+            headers.push((name.to_ascii_lowercase(), String::from(value.trim())));
+        }
+        Ok(Some((Head { status, headers }, end + 4)))
+    }
+
+    /// First value of a header field, by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(field, _)| field.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        matches!(self.status, 301 | 302 | 303 | 307 | 308)
+    }
+
+    /// How the body that follows this head is framed
+    pub fn framing(&self) -> Result<Framing, FetchError> {
+        if matches!(self.status, 100..=199 | 204 | 304) {
+            return Ok(Framing::Length(0));
+        }
+        if let Some(coding) = self.header("transfer-encoding") {
+            // Transfer-Encoding overrides Content-Length; only a final
+            // chunked coding has a known end
+            let last = coding.rsplit(',').next().unwrap_or("").trim();
+            return Ok(if last.eq_ignore_ascii_case("chunked") { Framing::Chunked } else { Framing::UntilClose });
+        }
+
+        let mut length = None;
+        for (_, value) in self.headers.iter().filter(|(name, _)| name == "content-length") {
+            let value = value.parse::<u64>().map_err(|_| FetchError::Malformed)?;
+            if length.is_some_and(|length| length != value) {
+                return Err(FetchError::Malformed);
+            }
+            length = Some(value);
+        }
+        Ok(length.map_or(Framing::UntilClose, Framing::Length))
+    }
+
+    /// `bytes start-end/total` of a 206 response
+    pub fn content_range(&self) -> Option<ContentRange> {
+        let (range, total) = self.header("content-range")?.strip_prefix("bytes ")?.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+        let total = if total == "*" { None } else { Some(total.parse().ok()?) };
+        if end < start || total.is_some_and(|total| end >= total) {
+            return None;
+        }
+        Some(ContentRange { start, end, total })
+    }
+
+    /// Length of the resource from the `bytes */total` of a 416 response
+    pub fn unsatisfied_length(&self) -> Option<u64> {
+        self.header("content-range")?.strip_prefix("bytes */")?.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    Length(u64),
+    Chunked,
+    UntilClose,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailer,
+}
+
+pub struct BodyDecoder {
+    framing: Framing,
+    /// Body bytes still expected for Content-Length framing
+    remaining: u64,
+    chunk: ChunkState,
+    /// Partial chunk-size or trailer line
+    line: Vec<u8>,
+    done: bool,
+}
+
+impl BodyDecoder {
+    pub fn new(framing: Framing) -> Self {
+        let remaining = match framing {
+            Framing::Length(length) => length,
+            _ => 0,
+        };
+        let done = framing == Framing::Length(0);
+        Self { framing, remaining, chunk: ChunkState::Size, line: Vec::new(), done }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Feed received bytes and hand the body bytes in them to `out`.
+    /// Returns true once the body is complete; bytes after its end are
+    /// ignored
+    pub fn feed(
+        &mut self,
+        mut data: &[u8],
+        out: &mut dyn FnMut(&[u8]) -> Result<(), FetchError>,
+    ) -> Result<bool, FetchError> {
+        while !data.is_empty() && !self.done {
+            match self.framing {
+                Framing::UntilClose => {
+                    out(data)?;
+                    data = &[];
+                }
+                Framing::Length(_) => {
+                    let count = (self.remaining.min(data.len() as u64)) as usize;
+                    out(&data[..count])?;
+                    data = &data[count..];
+                    self.remaining -= count as u64;
+                    self.done = self.remaining == 0;
+                }
+                Framing::Chunked => match self.chunk {
+                    ChunkState::Data(remaining) => {
+                        let count = (remaining.min(data.len() as u64)) as usize;
+                        out(&data[..count])?;
+                        data = &data[count..];
+                        self.chunk = if remaining == count as u64 {
+                            ChunkState::DataEnd
+                        } else {
+                            ChunkState::Data(remaining - count as u64)
+                        };
+                    }
+                    state => {
+                        let line = match self.take_line(&mut data)? {
+                            Some(line) => line,
+                            None => break,
+                        };
+                        match state {
+                            ChunkState::Size => {
+                                let size = line.split(|&byte| byte == b';').next().unwrap_or(&[]);
+                                let size = core::str::from_utf8(size).map_err(|_| FetchError::Malformed)?.trim();
+                                let size = u64::from_str_radix(size, 16).map_err(|_| FetchError::Malformed)?;
+                                self.chunk = if size == 0 { ChunkState::Trailer } else { ChunkState::Data(size) };
+                            }
+                            ChunkState::DataEnd if line.is_empty() => self.chunk = ChunkState::Size,
+                            ChunkState::DataEnd => return Err(FetchError::Malformed),
+                            _ => self.done = line.is_empty(),
+                        }
+                    }
+                },
+            }
+        }
+        Ok(self.done)
+    }
+
+    /// The server closed the connection; only until-close bodies end so
+    pub fn finish_on_close(&mut self) -> Result<(), FetchError> {
+        if self.framing == Framing::UntilClose {
+            self.done = true;
+        }
+        if self.done {
+            Ok(())
+        } else {
+            Err(FetchError::Closed)
+        }
+    }
+
+    /// Complete line without its CRLF, or None with the partial line kept
+    fn take_line(&mut self, data: &mut &[u8]) -> Result<Option<Vec<u8>>, FetchError> {
+        let (part, complete) = match data.iter().position(|&byte| byte == b'\n') {
+            Some(index) => (&data[..index], Some(index + 1)),
+            None => (*data, None),
+        };
+        if self.line.len() + part.len() > MAX_LINE {
+            return Err(FetchError::Malformed);
+        }
+        self.line.extend_from_slice(part);
+        *data = &data[complete.unwrap_or(data.len())..];
+        if complete.is_none() {
+            return Ok(None);
+        }
+        let mut line = core::mem::take(&mut self.line);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(framing: Framing, pieces: &[&[u8]]) -> Result<(Vec<u8>, bool), FetchError> {
+        let mut decoder = BodyDecoder::new(framing);
+        let mut body = Vec::new();
+        let mut done = decoder.is_done();
+        for piece in pieces {
+            done = decoder.feed(piece, &mut |data| {
+                body.extend_from_slice(data);
+                Ok(())
+            })?;
+        }
+        Ok((body, done))
+    }
+
+    #[test]
+    fn test_parses_heads() {
+        let data =
+            b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 100-199/1000\r\nContent-Length: 100\r\n\r\nbody";
+        assert_eq!(Head::parse(&data[..20]), Ok(None));
+        let (head, length) = Head::parse(data).unwrap().unwrap();
+        assert_eq!((head.status, length), (206, data.len() - 4));
+        assert_eq!(head.header("CONTENT-LENGTH"), Some("100"));
+        assert_eq!(head.framing(), Ok(Framing::Length(100)));
+        assert_eq!(head.content_range(), Some(ContentRange { start: 100, end: 199, total: Some(1000) }));
+
+        let (head, _) =
+            Head::parse(b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */1000\r\n\r\n").unwrap().unwrap();
+        assert_eq!(head.unsatisfied_length(), Some(1000));
+
+        let (head, _) =
+            Head::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nTransfer-Encoding: gzip, chunked\r\n\r\n")
+                .unwrap()
+                .unwrap();
+        assert_eq!(head.framing(), Ok(Framing::Chunked));
+        let (head, _) =
+            Head::parse(b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n").unwrap().unwrap();
+        assert_eq!(head.framing(), Err(FetchError::Malformed));
+
+        assert_eq!(Head::parse(b"SSH-2.0-OpenSSH\r\n\r\n"), Err(FetchError::Malformed));
+        assert_eq!(Head::parse(b"HTTP/1.1 200 OK\r\nX-A: 1\r\n folded\r\n\r\n"), Err(FetchError::Malformed));
+    }
+
+    #[test]
+    fn test_decodes_bodies() {
+        let chunked: &[u8] =
+            b"4;name=value\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\nExpires: never\r\n\r\nextra";
+        // Split at every position so each state sees partial input
+        for split in 0..chunked.len() {
+            let (body, done) = decode(Framing::Chunked, &[&chunked[..split], &chunked[split..]]).unwrap();
+            assert_eq!(body, b"Wikipedia in\r\n\r\nchunks.");
+            assert!(done);
+        }
+        assert_eq!(decode(Framing::Chunked, &[b"4\r\nWikiXX\r\n"]), Err(FetchError::Malformed));
+        assert_eq!(decode(Framing::Chunked, &[b"zz\r\n"]), Err(FetchError::Malformed));
+
+        assert_eq!(decode(Framing::Length(3), &[b"ab", b"cdef"]), Ok((b"abc".to_vec(), true)));
+        assert_eq!(decode(Framing::Length(0), &[]), Ok((Vec::new(), true)));
+
+        let mut decoder = BodyDecoder::new(Framing::Length(10));
+        decoder.feed(b"short", &mut |_| Ok(())).unwrap();
+        assert_eq!(decoder.finish_on_close(), Err(FetchError::Closed));
+        let mut decoder = BodyDecoder::new(Framing::UntilClose);
+        decoder.feed(b"anything", &mut |_| Ok(())).unwrap();
+        assert_eq!(decoder.finish_on_close(), Ok(()));
+    }
+}
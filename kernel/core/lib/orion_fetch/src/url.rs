@@ -0,0 +1,197 @@
+/*
+ * Orion Operating System - Fetch URLs
+ *
+ * http and https URLs as the fetch client needs them: scheme, host, port
+ * and the request target (path and query). User information, IPv6
+ * literals and fragments are not part of what a download needs; the
+ * first two are rejected and fragments are dropped.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::FetchError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    pub fn default_port(self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub scheme: Scheme,
+    /// Lower-case host name or dotted IPv4 address
+    pub host: String,
+    pub port: u16,
+    /// Request target, always starting with '/'
+    pub path: String,
+}
+
+/// Drop a fragment, which never goes to the server
+fn strip_fragment(text: &str) -> &str {
+    text.split('#').next().unwrap_or("")
+}
+
+/// Resolve "." and ".." segments of an absolute path (RFC 3986 5.2.4)
+fn remove_dot_segments(path: &str) -> String {
+    let (path, query) = match path.find('?') {
+        Some(index) => path.split_at(index),
+        None => (path, ""),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let mut directory = false;
+    for segment in path.split('/').skip(1) {
+        directory = false;
+        match segment {
+            "." => directory = true,
+            ".." => {
+                segments.pop();
+                directory = true;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let mut out = String::with_capacity(path.len() + query.len());
+    for segment in &segments {
+        out.push('/');
+        out.push_str(segment);
+    }
+    if directory || out.is_empty() {
+        out.push('/');
+    }
+    out.push_str(query);
+    out
+}
+
+impl Url {
+    pub fn parse(text: &str) -> Result<Url, FetchError> {
+        let text = strip_fragment(text.trim());
+        let (scheme, rest) = text.split_once("://").ok_or(FetchError::InvalidUrl)?;
+        let scheme = if scheme.eq_ignore_ascii_case("http") {
+            Scheme::Http
+        } else if scheme.eq_ignore_ascii_case("https") {
+            Scheme::Https
+        } else {
+            return Err(FetchError::UnsupportedScheme);
+        };
+
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        if authority.contains(['@', '[', ']', ' ']) {
+            return Err(FetchError::InvalidUrl);
+        }
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, "")) => (host, scheme.default_port()),
+            Some((host, port)) => match port.parse::<u16>() {
+                Ok(port) if port != 0 => (host, port),
+                _ => return Err(FetchError::InvalidUrl),
+            },
+            None => (authority, scheme.default_port()),
+        };
+        if host.is_empty() {
+            return Err(FetchError::InvalidUrl);
+        }
+
+        let path = if target.starts_with('/') { remove_dot_segments(target) } else { format!("/{}", target) };
+        Ok(Url { scheme, host: host.to_ascii_lowercase(), port, path })
+    }
+
+    /// Resolve a redirect Location against this URL
+    pub fn join(&self, location: &str) -> Result<Url, FetchError> {
+        let location = strip_fragment(location.trim());
+        if location.contains("://") {
+            return Url::parse(location);
+        }
+        if location.starts_with("//") {
+            return Url::parse(&format!("{}:{}", self.scheme.as_str(), location));
+        }
+
+        let path = if location.starts_with('/') {
+            location.to_string()
+        } else {
+            let base = self.path.split('?').next().unwrap_or("/");
+            if location.is_empty() {
+                self.path.clone()
+            } else if location.starts_with('?') {
+                format!("{}{}", base, location)
+            } else {
+                let directory = &base[..base.rfind('/').map_or(0, |index| index + 1)];
+                format!("{}{}", directory, location)
+            }
+        };
+        Ok(Url { path: remove_dot_segments(&path), ..self.clone() })
+    }
+
+    /// Host header value; the port only appears when it is not the default
+    pub fn authority(&self) -> String {
+        if self.port == self.scheme.default_port() {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}{}", self.scheme.as_str(), self.authority(), self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_and_joins() {
+        let url = Url::parse("HTTPS://Mirror.Orion-OS.dev:8443/pkg/../images/os.img?arch=x86_64#top").unwrap();
+        assert_eq!(url.scheme, Scheme::Https);
+        assert_eq!(url.host, "mirror.orion-os.dev");
+        assert_eq!(url.port, 8443);
+        assert_eq!(url.path, "/images/os.img?arch=x86_64");
+        assert_eq!(url.to_string(), "https://mirror.orion-os.dev:8443/images/os.img?arch=x86_64");
+
+        let bare = Url::parse("http://10.0.0.1").unwrap();
+        assert_eq!((bare.port, bare.path.as_str(), bare.authority().as_str()), (80, "/", "10.0.0.1"));
+
+        assert_eq!(url.join("os.img.sig").unwrap().path, "/images/os.img.sig");
+        assert_eq!(url.join("../keys/ca.der").unwrap().path, "/keys/ca.der");
+        assert_eq!(url.join("/v2/os.img").unwrap().path, "/v2/os.img");
+        assert_eq!(url.join("?arch=aarch64").unwrap().path, "/images/os.img?arch=aarch64");
+        let other = url.join("//cdn.orion-os.dev/os.img").unwrap();
+        assert_eq!((other.scheme, other.host.as_str(), other.port), (Scheme::Https, "cdn.orion-os.dev", 443));
+        assert_eq!(url.join("http://10.0.0.2:8080/a").unwrap().to_string(), "http://10.0.0.2:8080/a");
+
+        assert_eq!(Url::parse("ftp://host/file"), Err(FetchError::UnsupportedScheme));
+        assert_eq!(Url::parse("http://user@host/"), Err(FetchError::InvalidUrl));
+        assert_eq!(Url::parse("http://host:0/"), Err(FetchError::InvalidUrl));
+        assert_eq!(Url::parse("host/file"), Err(FetchError::InvalidUrl));
+    }
+}
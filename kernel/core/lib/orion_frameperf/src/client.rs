@@ -0,0 +1,134 @@
+/*
+ * Orion Operating System - Frame Performance Client
+ *
+ * Client side of the frame performance requests, used by orion-top and
+ * the compositor. The transport is a trait so callers can use the
+ * driver's IPC channel and tests call straight into handle_control.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::control::{
+    decode_jank, decode_report, read_u32, FRAME_CTRL_JANKS, FRAME_CTRL_QUERY, FRAME_CTRL_RESET, FRAME_CTRL_SET_TARGET,
+    JANK_SIZE, REPORT_SIZE, STATUS_EIO, STATUS_OK,
+};
+use crate::frames::Jank;
+use crate::monitor::FrameReport;
+
+/// Carries a request to a driver and returns its reply, None when the
+/// driver did not answer
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+pub struct FrameClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> FrameClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, opcode: u32, argument: &[u8]) -> Result<Vec<u8>, i32> {
+        let mut request = Vec::with_capacity(4 + argument.len());
+        request.extend_from_slice(&opcode.to_le_bytes());
+        request.extend_from_slice(argument);
+        let response = self.transport.call(&request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    pub fn query(&mut self) -> Result<FrameReport, i32> {
+        let payload = self.call(FRAME_CTRL_QUERY, &[])?;
+        if payload.len() < REPORT_SIZE {
+            return Err(STATUS_EIO);
+        }
+        decode_report(&payload).ok_or(STATUS_EIO)
+    }
+
+    /// Up to `max` janks of the frames after `after`
+    pub fn janks(&mut self, after: u64, max: u32) -> Result<Vec<Jank>, i32> {
+        let mut argument = after.to_le_bytes().to_vec();
+        argument.extend_from_slice(&max.to_le_bytes());
+        let payload = self.call(FRAME_CTRL_JANKS, &argument)?;
+        let count = read_u32(&payload, 0).ok_or(STATUS_EIO)? as usize;
+        let janks = payload
+            .get(4..)
+            .ok_or(STATUS_EIO)?
+            .chunks_exact(JANK_SIZE)
+            .take(count)
+            .map(decode_jank)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(STATUS_EIO)?;
+        if janks.len() != count {
+            return Err(STATUS_EIO);
+        }
+        Ok(janks)
+    }
+
+    pub fn set_target(&mut self, target_ns: u64) -> Result<(), i32> {
+        self.call(FRAME_CTRL_SET_TARGET, &target_ns.to_le_bytes()).map(|_| ())
+    }
+
+    pub fn reset(&mut self) -> Result<(), i32> {
+        self.call(FRAME_CTRL_RESET, &[]).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{handle_control, STATUS_EINVAL};
+    use crate::monitor::FrameMonitor;
+
+    const MS: u64 = 1_000_000;
+
+    struct Direct<'a> {
+        monitor: &'a mut FrameMonitor,
+        now: u64,
+    }
+
+    impl Transport for Direct<'_> {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            Some(handle_control(self.monitor, request, self.now))
+        }
+    }
+
+    #[test]
+    fn test_reports_through_control_requests() {
+        let mut monitor = FrameMonitor::new(16 * MS);
+        let queue = monitor.queue();
+        queue.submitted(MS);
+        for frame in 0..=10u64 {
+            monitor.present(MS + frame * 16 * MS + if frame >= 5 { 30 * MS } else { 0 });
+        }
+        queue.completed(1001 * MS);
+
+        let mut client = FrameClient::new(Direct { monitor: &mut monitor, now: 1001 * MS });
+        let report = client.query().unwrap();
+        assert_eq!((report.target_ns, report.frames, report.janks), (16 * MS, 10, 1));
+        assert_eq!((report.percentiles.p50_ns, report.percentiles.max_ns), (16 * MS, 46 * MS));
+        assert_eq!((report.utilization.busy_ppm, report.queue_depth), (1_000_000, 0));
+        assert_eq!(report.fps_milli(), 1_000_000_000_000 / report.percentiles.mean_ns);
+
+        let janks = client.janks(0, 8).unwrap();
+        assert_eq!(janks, [Jank { frame: 5, time_ns: 111 * MS, duration_ns: 46 * MS }]);
+        assert!(client.janks(5, 8).unwrap().is_empty());
+
+        assert_eq!(client.set_target(100), Err(STATUS_EINVAL));
+        client.set_target(8 * MS).unwrap();
+        client.reset().unwrap();
+        let report = client.query().unwrap();
+        assert_eq!((report.target_ns, report.frames, report.janks), (8 * MS, 0, 0));
+    }
+}
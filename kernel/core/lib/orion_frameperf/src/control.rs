@@ -0,0 +1,164 @@
+/*
+ * Orion Operating System - Frame Performance Control
+ *
+ * Requests display drivers accept through an ioctl of command
+ * FRAME_IOCTL_CONTROL to report on their frames. All fields are
+ * little-endian; every request starts with a 32-bit opcode and every
+ * reply with a 32-bit signed status (0 or a negative errno).
+ *
+ *   QUERY                       -> target_ns:u64 frames:u64 janks:u64
+ *                                  p50:u64 p90:u64 p99:u64 max:u64 mean:u64
+ *                                  busy_ppm:u32 mean_depth_milli:u32
+ *                                  queue_depth:u32 pad:u32
+ *   JANKS      after:u64 max:u32 -> count:u32 {frame:u64 time_ns:u64
+ *                                  duration_ns:u64}*
+ *   SET_TARGET target_ns:u64    -> (empty)
+ *   RESET                       -> (empty)
+ *
+ * Frame times are in nanoseconds. JANKS answers the janks still kept of
+ * the frames after `after`, oldest first; a reader passes the last frame
+ * it saw to get only the new ones. SET_TARGET sets the frame time aimed
+ * at, which the compositor knows from the refresh rate it runs at.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::frames::{Jank, Percentiles};
+use crate::monitor::{FrameMonitor, FrameReport};
+use crate::occupancy::Utilization;
+
+/// Ioctl command selecting frame performance requests
+pub const FRAME_IOCTL_CONTROL: u32 = 0x3040;
+
+// Opcodes
+pub const FRAME_CTRL_QUERY: u32 = 0x5001;
+pub const FRAME_CTRL_JANKS: u32 = 0x5002;
+pub const FRAME_CTRL_SET_TARGET: u32 = 0x5003;
+pub const FRAME_CTRL_RESET: u32 = 0x5004;
+
+/// Most janks one JANKS returns
+pub const MAX_READ_JANKS: u32 = 64;
+
+pub const REPORT_SIZE: usize = 80;
+pub const JANK_SIZE: usize = 24;
+
+/// Shortest and longest frame time SET_TARGET accepts, 1000 Hz to 1 Hz
+pub const MIN_TARGET_NS: u64 = 1_000_000;
+pub const MAX_TARGET_NS: u64 = 1_000_000_000;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EINVAL: i32 = -22;
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+pub fn encode_report(report: &FrameReport, out: &mut Vec<u8>) {
+    let percentiles = &report.percentiles;
+    for value in [
+        report.target_ns,
+        report.frames,
+        report.janks,
+        percentiles.p50_ns,
+        percentiles.p90_ns,
+        percentiles.p99_ns,
+        percentiles.max_ns,
+        percentiles.mean_ns,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [report.utilization.busy_ppm, report.utilization.mean_depth_milli, report.queue_depth, 0] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+pub fn decode_report(data: &[u8]) -> Option<FrameReport> {
+    Some(FrameReport {
+        target_ns: read_u64(data, 0)?,
+        frames: read_u64(data, 8)?,
+        janks: read_u64(data, 16)?,
+        percentiles: Percentiles {
+            p50_ns: read_u64(data, 24)?,
+            p90_ns: read_u64(data, 32)?,
+            p99_ns: read_u64(data, 40)?,
+            max_ns: read_u64(data, 48)?,
+            mean_ns: read_u64(data, 56)?,
+        },
+        utilization: Utilization { busy_ppm: read_u32(data, 64)?, mean_depth_milli: read_u32(data, 68)? },
+        queue_depth: read_u32(data, 72)?,
+    })
+}
+
+pub fn encode_jank(jank: &Jank, out: &mut Vec<u8>) {
+    out.extend_from_slice(&jank.frame.to_le_bytes());
+    out.extend_from_slice(&jank.time_ns.to_le_bytes());
+    out.extend_from_slice(&jank.duration_ns.to_le_bytes());
+}
+
+pub fn decode_jank(data: &[u8]) -> Option<Jank> {
+    Some(Jank { frame: read_u64(data, 0)?, time_ns: read_u64(data, 8)?, duration_ns: read_u64(data, 16)? })
+}
+
+fn control(monitor: &mut FrameMonitor, request: &[u8], now_ns: u64, out: &mut Vec<u8>) -> i32 {
+    let Some(opcode) = read_u32(request, 0) else {
+        return STATUS_EINVAL;
+    };
+    match opcode {
+        FRAME_CTRL_QUERY => {
+            encode_report(&monitor.report(now_ns), out);
+            STATUS_OK
+        }
+        FRAME_CTRL_JANKS => match (read_u64(request, 4), read_u32(request, 12)) {
+            (Some(after), Some(max)) => {
+                let janks = monitor.janks(after, max.min(MAX_READ_JANKS) as usize);
+                out.extend_from_slice(&(janks.len() as u32).to_le_bytes());
+                for jank in &janks {
+                    encode_jank(jank, out);
+                }
+                STATUS_OK
+            }
+            _ => STATUS_EINVAL,
+        },
+        FRAME_CTRL_SET_TARGET => match read_u64(request, 4) {
+            Some(target) if (MIN_TARGET_NS..=MAX_TARGET_NS).contains(&target) => {
+                monitor.set_target(target);
+                STATUS_OK
+            }
+            _ => STATUS_EINVAL,
+        },
+        FRAME_CTRL_RESET => {
+            monitor.reset();
+            STATUS_OK
+        }
+        _ => STATUS_EINVAL,
+    }
+}
+
+/// Serve a frame performance request and build the reply: i32 status
+/// followed by the payload of the operation
+pub fn handle_control(monitor: &mut FrameMonitor, request: &[u8], now_ns: u64) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let status = control(monitor, request, now_ns, &mut payload);
+    let mut reply = Vec::with_capacity(4 + payload.len());
+    reply.extend_from_slice(&status.to_le_bytes());
+    if status == STATUS_OK {
+        reply.extend_from_slice(&payload);
+    }
+    reply
+}
@@ -0,0 +1,193 @@
+/*
+ * Orion Operating System - Frame Times
+ *
+ * A frame time is the interval between two presents. The latest
+ * FRAME_WINDOW of them are kept for percentiles; a frame longer than
+ * JANK_FACTOR times the target is a jank, counted and kept in a short log
+ * with its frame number so a reader can ask for the janks it has not seen.
+ * A gap longer than IDLE_GAP_NS means nothing changed on screen rather
+ * than a slow frame, and starts timing afresh.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Frame times kept for the percentiles, ten seconds at 60 Hz
+pub const FRAME_WINDOW: usize = 600;
+
+/// Janks kept in the log
+pub const JANK_LOG: usize = 64;
+
+/// A frame over this many times the target is a jank
+pub const JANK_FACTOR: u64 = 2;
+
+/// Longer intervals between presents are idle time, not frames
+pub const IDLE_GAP_NS: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jank {
+    /// Number of the frame, counted from 1 since the last reset
+    pub frame: u64,
+    /// When it was presented
+    pub time_ns: u64,
+    pub duration_ns: u64,
+}
+
+/// Frame-time percentiles over the window, 0 while it is empty
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Percentiles {
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub max_ns: u64,
+    pub mean_ns: u64,
+}
+
+pub struct FrameTimes {
+    target_ns: u64,
+    window: VecDeque<u64>,
+    last_present: Option<u64>,
+    /// Frames timed since the last reset
+    frames: u64,
+    janks: u64,
+    log: VecDeque<Jank>,
+}
+
+impl FrameTimes {
+    pub fn new(target_ns: u64) -> Self {
+        Self {
+            target_ns: target_ns.max(1),
+            window: VecDeque::with_capacity(FRAME_WINDOW),
+            last_present: None,
+            frames: 0,
+            janks: 0,
+            log: VecDeque::with_capacity(JANK_LOG),
+        }
+    }
+
+    pub fn target_ns(&self) -> u64 {
+        self.target_ns
+    }
+
+    /// Frame time aimed at, the refresh interval of the display
+    pub fn set_target(&mut self, target_ns: u64) {
+        self.target_ns = target_ns.max(1);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn janks(&self) -> u64 {
+        self.janks
+    }
+
+    /// Record a present; answers the frame time, None for the first frame
+    /// after a reset or an idle gap
+    pub fn present(&mut self, now_ns: u64) -> Option<u64> {
+        let last = self.last_present.replace(now_ns)?;
+        let duration = now_ns.saturating_sub(last);
+        if duration > IDLE_GAP_NS {
+            return None;
+        }
+
+        self.frames += 1;
+        if self.window.len() == FRAME_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(duration);
+        if duration > JANK_FACTOR * self.target_ns {
+            self.janks += 1;
+            if self.log.len() == JANK_LOG {
+                self.log.pop_front();
+            }
+            self.log.push_back(Jank { frame: self.frames, time_ns: now_ns, duration_ns: duration });
+        }
+        Some(duration)
+    }
+
+    /// Forget every frame and jank, keeping the target
+    pub fn reset(&mut self) {
+        *self = Self::new(self.target_ns);
+    }
+
+    pub fn percentiles(&self) -> Percentiles {
+        if self.window.is_empty() {
+            return Percentiles::default();
+        }
+        let mut sorted: Vec<u64> = self.window.iter().copied().collect();
+        sorted.sort_unstable();
+        // Nearest rank
+        let rank = |percent: usize| sorted[(sorted.len() * percent).div_ceil(100) - 1];
+        Percentiles {
+            p50_ns: rank(50),
+            p90_ns: rank(90),
+            p99_ns: rank(99),
+            max_ns: sorted[sorted.len() - 1],
+            mean_ns: sorted.iter().sum::<u64>() / sorted.len() as u64,
+        }
+    }
+
+    /// Janks kept of the frames after `frame`, oldest first
+    pub fn janks_after(&self, frame: u64) -> impl Iterator<Item = &Jank> {
+        self.log.iter().filter(move |jank| jank.frame > frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_reports_percentiles_and_janks() {
+        let mut times = FrameTimes::new(16 * MS);
+        let mut now = 5 * MS;
+        assert_eq!(times.present(now), None);
+        for frame in 1..=100u64 {
+            now += match frame {
+                50 => 40 * MS,
+                90..=99 => 20 * MS,
+                100 => 33 * MS,
+                _ => 16 * MS,
+            };
+            times.present(now);
+        }
+
+        let percentiles = times.percentiles();
+        assert_eq!((percentiles.p50_ns, percentiles.p90_ns, percentiles.p99_ns), (16 * MS, 20 * MS, 33 * MS));
+        assert_eq!(percentiles.max_ns, 40 * MS);
+        assert_eq!(percentiles.mean_ns, (88 * 16 + 40 + 10 * 20 + 33) * MS / 100);
+
+        // 40 ms is past twice the target, 33 ms just so, 20 ms is not
+        assert_eq!((times.frames(), times.janks()), (100, 2));
+        let janks: Vec<u64> = times.janks_after(0).map(|jank| jank.frame).collect();
+        assert_eq!(janks, [50, 100]);
+        assert_eq!(times.janks_after(50).count(), 1);
+    }
+
+    #[test]
+    fn test_skips_idle_gaps_and_keeps_a_window() {
+        let mut times = FrameTimes::new(16 * MS);
+        times.present(0);
+        assert_eq!(times.present(16 * MS), Some(16 * MS));
+        // Nothing on screen changed for two seconds
+        assert_eq!(times.present(2016 * MS), None);
+        assert_eq!(times.present(2032 * MS), Some(16 * MS));
+        assert_eq!(times.janks(), 0);
+
+        for frame in 0..FRAME_WINDOW as u64 {
+            times.present(2032 * MS + (frame + 1) * 10 * MS);
+        }
+        assert_eq!(times.percentiles().max_ns, 10 * MS);
+        times.reset();
+        assert_eq!((times.frames(), times.percentiles(), times.target_ns()), (0, Percentiles::default(), 16 * MS));
+    }
+}
@@ -0,0 +1,35 @@
+/*
+ * Orion Operating System - Frame Performance
+ *
+ * How smoothly a display driver puts frames on screen. The driver tells
+ * its FrameMonitor every time a frame is presented and hands the monitor's
+ * QueueOccupancy to its command queue; the monitor keeps the latest frame
+ * times for percentiles, counts the frames that took more than twice the
+ * target (janks) and keeps the last of them, and estimates how busy the
+ * GPU is from the time its command queue had work outstanding.
+ *
+ * orion-top and the compositor read all of it through the control
+ * requests (see control.rs), with FrameClient.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod client;
+pub mod control;
+pub mod frames;
+pub mod monitor;
+pub mod occupancy;
+
+pub use client::{FrameClient, Transport};
+pub use control::{handle_control, FRAME_IOCTL_CONTROL};
+pub use frames::{FrameTimes, Jank, Percentiles, FRAME_WINDOW, IDLE_GAP_NS, JANK_FACTOR, JANK_LOG};
+pub use monitor::{FrameMonitor, FrameReport};
+pub use occupancy::{QueueOccupancy, Utilization, UTILIZATION_WINDOW_NS};
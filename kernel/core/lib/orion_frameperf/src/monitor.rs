@@ -0,0 +1,83 @@
+/*
+ * Orion Operating System - Frame Monitor
+ *
+ * The frame times and the command queue occupancy of one display driver,
+ * and the report the control requests answer with.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+
+use crate::frames::{FrameTimes, Jank, Percentiles};
+use crate::occupancy::{QueueOccupancy, Utilization};
+
+/// What QUERY answers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameReport {
+    pub target_ns: u64,
+    /// Frames timed since the last reset
+    pub frames: u64,
+    pub janks: u64,
+    pub percentiles: Percentiles,
+    pub utilization: Utilization,
+    /// Commands outstanding now
+    pub queue_depth: u32,
+}
+
+impl FrameReport {
+    /// Frames per second at the mean frame time, in thousandths
+    pub fn fps_milli(&self) -> u64 {
+        1_000_000_000_000u64.checked_div(self.percentiles.mean_ns).unwrap_or(0)
+    }
+}
+
+pub struct FrameMonitor {
+    frames: FrameTimes,
+    queue: Rc<QueueOccupancy>,
+}
+
+impl FrameMonitor {
+    pub fn new(target_ns: u64) -> Self {
+        Self { frames: FrameTimes::new(target_ns), queue: Rc::new(QueueOccupancy::new()) }
+    }
+
+    /// Occupancy to hand the command queue, which reports to it
+    pub fn queue(&self) -> Rc<QueueOccupancy> {
+        self.queue.clone()
+    }
+
+    pub fn present(&mut self, now_ns: u64) -> Option<u64> {
+        self.frames.present(now_ns)
+    }
+
+    pub fn set_target(&mut self, target_ns: u64) {
+        self.frames.set_target(target_ns);
+    }
+
+    /// Forget the frames and janks; the queue keeps its depth
+    pub fn reset(&mut self) {
+        self.frames.reset();
+    }
+
+    pub fn report(&self, now_ns: u64) -> FrameReport {
+        FrameReport {
+            target_ns: self.frames.target_ns(),
+            frames: self.frames.frames(),
+            janks: self.frames.janks(),
+            percentiles: self.frames.percentiles(),
+            utilization: self.queue.utilization(now_ns),
+            queue_depth: self.queue.depth(),
+        }
+    }
+
+    /// Up to `max` of the janks kept after frame `after`, oldest first
+    pub fn janks(&self, after: u64, max: usize) -> Vec<Jank> {
+        self.frames.janks_after(after).take(max).copied().collect()
+    }
+}
@@ -0,0 +1,131 @@
+/*
+ * Orion Operating System - Queue Occupancy
+ *
+ * GPU utilization estimated from the command queue: the GPU is taken to be
+ * busy while at least one submitted command has not completed. The queue
+ * reports each submission and completion; the time with work outstanding
+ * and the mean number of commands outstanding are added up over windows
+ * of at least UTILIZATION_WINDOW_NS, and the last full window is what is
+ * reported, so a reading does not swing with every command.
+ *
+ * The queue and the monitor share one QueueOccupancy, hence the cells.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::cell::Cell;
+
+/// Shortest window utilization is measured over
+pub const UTILIZATION_WINDOW_NS: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Utilization {
+    /// Share of the window with commands outstanding, parts per million
+    pub busy_ppm: u32,
+    /// Mean commands outstanding over the window, in thousandths
+    pub mean_depth_milli: u32,
+}
+
+#[derive(Default)]
+pub struct QueueOccupancy {
+    depth: Cell<u32>,
+    /// Last time the depth changed or was accounted for, 0 before the
+    /// first event
+    since: Cell<u64>,
+    window_start: Cell<u64>,
+    busy_ns: Cell<u64>,
+    /// Depth integrated over the window, command-nanoseconds
+    depth_ns: Cell<u128>,
+    last: Cell<Utilization>,
+}
+
+impl QueueOccupancy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Commands outstanding now
+    pub fn depth(&self) -> u32 {
+        self.depth.get()
+    }
+
+    pub fn submitted(&self, now_ns: u64) {
+        self.advance(now_ns);
+        self.depth.set(self.depth.get().saturating_add(1));
+    }
+
+    pub fn completed(&self, now_ns: u64) {
+        self.advance(now_ns);
+        self.depth.set(self.depth.get().saturating_sub(1));
+    }
+
+    /// Utilization over the last full window
+    pub fn utilization(&self, now_ns: u64) -> Utilization {
+        self.advance(now_ns);
+        self.last.get()
+    }
+
+    /// Account for the time since the last event at the current depth,
+    /// closing the window once it is long enough
+    fn advance(&self, now_ns: u64) {
+        if self.since.get() == 0 {
+            self.since.set(now_ns.max(1));
+            self.window_start.set(now_ns);
+            return;
+        }
+        let elapsed = now_ns.saturating_sub(self.since.get());
+        self.since.set(now_ns.max(self.since.get()));
+        let depth = self.depth.get();
+        if depth > 0 {
+            self.busy_ns.set(self.busy_ns.get() + elapsed);
+            self.depth_ns.set(self.depth_ns.get() + elapsed as u128 * depth as u128);
+        }
+
+        let window = now_ns.saturating_sub(self.window_start.get());
+        if window < UTILIZATION_WINDOW_NS {
+            return;
+        }
+        let busy_ppm = (self.busy_ns.get() as u128 * 1_000_000 / window as u128).min(1_000_000) as u32;
+        let mean_depth_milli = (self.depth_ns.get() * 1000 / window as u128).min(u32::MAX as u128) as u32;
+        self.last.set(Utilization { busy_ppm, mean_depth_milli });
+        self.window_start.set(now_ns);
+        self.busy_ns.set(0);
+        self.depth_ns.set(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_measures_busy_share_per_window() {
+        let queue = QueueOccupancy::new();
+        let start = 1000 * MS;
+        assert_eq!(queue.utilization(start), Utilization::default());
+
+        // Two commands overlap for 100 ms, one alone for 200 ms more
+        queue.submitted(start);
+        queue.submitted(start + 100 * MS);
+        queue.completed(start + 200 * MS);
+        queue.completed(start + 400 * MS);
+        assert_eq!(queue.depth(), 0);
+        // The window is not over yet
+        assert_eq!(queue.utilization(start + 900 * MS), Utilization::default());
+        assert_eq!(queue.utilization(start + 1000 * MS), Utilization { busy_ppm: 400_000, mean_depth_milli: 500 });
+
+        // A queue never drained is fully busy
+        queue.submitted(start + 1000 * MS);
+        assert_eq!(queue.utilization(start + 3000 * MS).busy_ppm, 1_000_000);
+        // A stray completion does not wrap the depth
+        queue.completed(start + 3000 * MS);
+        queue.completed(start + 3000 * MS);
+        assert_eq!(queue.depth(), 0);
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * Orion Operating System - File Contents Encryption
+ *
+ * An encrypted file starts with a header holding its nonce and its size,
+ * followed by its contents in units of UNIT_SIZE bytes, each encrypted
+ * with AES-256-XTS under the key of the nonce and a tweak of its number.
+ * The last unit is zero-padded to a whole AES block. Any unit is read or
+ * rewritten alone; as with fscrypt, rewriting a unit shows which of its
+ * blocks changed to someone comparing the disk over time, and nothing
+ * authenticates the contents.
+ *
+ *   0    4   magic "OFCE"
+ *   4    4   format, 1
+ *   8    16  nonce
+ *   24   8   size of the contents
+ *   32       unit 0, unit 1, ...
+ *
+ * The size is in the clear, as it is on any file system; the header is
+ * rewritten after the units when a write extends the file.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+
+use orion_crypto::aes::{Xts, AES_BLOCK_SIZE};
+use orion_crypto::wipe;
+
+use crate::keys::{Nonce, NONCE_SIZE};
+
+pub const UNIT_SIZE: usize = 4096;
+pub const HEADER_SIZE: usize = 32;
+
+const HEADER_MAGIC: &[u8; 4] = b"OFCE";
+const HEADER_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileHeader {
+    pub nonce: Nonce,
+    pub size: u64,
+}
+
+impl FileHeader {
+    /// Header of a new, empty file; the nonce must be random
+    pub fn new(nonce: Nonce) -> Self {
+        Self { nonce, size: 0 }
+    }
+
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut out = [0u8; HEADER_SIZE];
+        out[..4].copy_from_slice(HEADER_MAGIC);
+        out[4..8].copy_from_slice(&HEADER_FORMAT.to_le_bytes());
+        out[8..24].copy_from_slice(&self.nonce);
+        out[24..32].copy_from_slice(&self.size.to_le_bytes());
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_SIZE || &data[..4] != HEADER_MAGIC || data[4..8] != HEADER_FORMAT.to_le_bytes() {
+            return None;
+        }
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&data[8..24]);
+        Some(Self { nonce, size: u64::from_le_bytes(data[24..32].try_into().unwrap()) })
+    }
+}
+
+/// The file holding an encrypted file on the file system below
+pub trait LowerFile {
+    type Error;
+    fn read_at(&mut self, offset: u64, buffer: &mut [u8]) -> Result<usize, Self::Error>;
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<usize, Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentsError<E> {
+    /// The file below failed
+    Lower(E),
+    /// The file below is shorter than its header says, or has no header
+    Corrupt,
+    /// The file below took fewer bytes than written
+    ShortWrite,
+}
+
+fn read_exact<F: LowerFile>(file: &mut F, offset: u64, buffer: &mut [u8]) -> Result<(), ContentsError<F::Error>> {
+    let mut done = 0;
+    while done < buffer.len() {
+        match file.read_at(offset + done as u64, &mut buffer[done..]).map_err(ContentsError::Lower)? {
+            0 => return Err(ContentsError::Corrupt),
+            count => done += count,
+        }
+    }
+    Ok(())
+}
+
+fn write_all<F: LowerFile>(file: &mut F, offset: u64, data: &[u8]) -> Result<(), ContentsError<F::Error>> {
+    let mut done = 0;
+    while done < data.len() {
+        match file.write_at(offset + done as u64, &data[done..]).map_err(ContentsError::Lower)? {
+            0 => return Err(ContentsError::ShortWrite),
+            count => done += count,
+        }
+    }
+    Ok(())
+}
+
+/// Bytes stored for unit `unit` of a file of `size` bytes
+fn stored_len(size: u64, unit: u64) -> usize {
+    let start = unit * UNIT_SIZE as u64;
+    let length = size.saturating_sub(start).min(UNIT_SIZE as u64) as usize;
+    length.next_multiple_of(AES_BLOCK_SIZE)
+}
+
+fn unit_offset(unit: u64) -> u64 {
+    HEADER_SIZE as u64 + unit * UNIT_SIZE as u64
+}
+
+/// Header of an encrypted file, None for an empty file that has none yet
+pub fn read_header<F: LowerFile>(file: &mut F) -> Result<Option<FileHeader>, ContentsError<F::Error>> {
+    let mut data = [0u8; HEADER_SIZE];
+    let mut done = 0;
+    while done < HEADER_SIZE {
+        match file.read_at(done as u64, &mut data[done..]).map_err(ContentsError::Lower)? {
+            0 => break,
+            count => done += count,
+        }
+    }
+    match done {
+        0 => Ok(None),
+        HEADER_SIZE => FileHeader::decode(&data).map(Some).ok_or(ContentsError::Corrupt),
+        _ => Err(ContentsError::Corrupt),
+    }
+}
+
+pub fn write_header<F: LowerFile>(file: &mut F, header: &FileHeader) -> Result<(), ContentsError<F::Error>> {
+    write_all(file, 0, &header.encode())
+}
+
+/// Read the contents at `offset` into `buffer`, up to the end of the file
+pub fn read<F: LowerFile>(
+    file: &mut F,
+    cipher: &Xts,
+    header: &FileHeader,
+    offset: u64,
+    buffer: &mut [u8],
+) -> Result<usize, ContentsError<F::Error>> {
+    if offset >= header.size {
+        return Ok(0);
+    }
+    let end = (offset + buffer.len() as u64).min(header.size);
+    let mut unit_data = vec![0u8; UNIT_SIZE];
+    let mut position = offset;
+    let result = loop {
+        if position >= end {
+            break Ok((end - offset) as usize);
+        }
+        let unit = position / UNIT_SIZE as u64;
+        let stored = &mut unit_data[..stored_len(header.size, unit)];
+        if let Err(error) = read_exact(file, unit_offset(unit), stored) {
+            break Err(error);
+        }
+        cipher.decrypt(unit, stored);
+
+        let within = (position % UNIT_SIZE as u64) as usize;
+        let count = ((end - position) as usize).min(UNIT_SIZE - within);
+        let at = (position - offset) as usize;
+        buffer[at..at + count].copy_from_slice(&unit_data[within..within + count]);
+        position += count as u64;
+    };
+    wipe(&mut unit_data);
+    result
+}
+
+/// Write `data` at `offset`, extending the file as needed. A gap left
+/// past the old end reads as zeros
+pub fn write<F: LowerFile>(
+    file: &mut F,
+    cipher: &Xts,
+    header: &mut FileHeader,
+    offset: u64,
+    data: &[u8],
+) -> Result<usize, ContentsError<F::Error>> {
+    if data.is_empty() {
+        return Ok(0);
+    }
+    let end = offset + data.len() as u64;
+    let size = header.size.max(end);
+    let mut unit_data = vec![0u8; UNIT_SIZE];
+    let mut unit = offset.min(header.size) / UNIT_SIZE as u64;
+    let result = loop {
+        let unit_start = unit * UNIT_SIZE as u64;
+        if unit_start >= end {
+            break Ok(());
+        }
+        // What the unit holds now, zeros past the old end
+        unit_data.fill(0);
+        let old = stored_len(header.size, unit);
+        if old > 0 {
+            if let Err(error) = read_exact(file, unit_offset(unit), &mut unit_data[..old]) {
+                break Err(error);
+            }
+            cipher.decrypt(unit, &mut unit_data[..old]);
+            let valid = (header.size - unit_start).min(UNIT_SIZE as u64) as usize;
+            unit_data[valid..].fill(0);
+        }
+
+        let from = offset.max(unit_start);
+        let to = end.min(unit_start + UNIT_SIZE as u64);
+        if from < to {
+            let source = (from - offset) as usize;
+            let target = (from - unit_start) as usize;
+            let count = (to - from) as usize;
+            unit_data[target..target + count].copy_from_slice(&data[source..source + count]);
+        }
+
+        let stored = &mut unit_data[..stored_len(size, unit)];
+        cipher.encrypt(unit, stored);
+        if let Err(error) = write_all(file, unit_offset(unit), stored) {
+            break Err(error);
+        }
+        unit += 1;
+    };
+    wipe(&mut unit_data);
+    result?;
+
+    if size != header.size {
+        header.size = size;
+        write_header(file, header)?;
+    }
+    Ok(data.len())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// File kept in memory
+    pub struct MemoryFile(pub Vec<u8>);
+
+    impl LowerFile for MemoryFile {
+        type Error = ();
+
+        fn read_at(&mut self, offset: u64, buffer: &mut [u8]) -> Result<usize, ()> {
+            let start = (offset as usize).min(self.0.len());
+            let count = buffer.len().min(self.0.len() - start);
+            buffer[..count].copy_from_slice(&self.0[start..start + count]);
+            Ok(count)
+        }
+
+        fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<usize, ()> {
+            let end = offset as usize + data.len();
+            if self.0.len() < end {
+                self.0.resize(end, 0);
+            }
+            self.0[offset as usize..end].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn test_contents_round_trip() {
+        let cipher = Xts::new(&[4; 64]);
+        let mut file = MemoryFile(Vec::new());
+        assert_eq!(read_header(&mut file), Ok(None));
+        let mut header = FileHeader::new([1; NONCE_SIZE]);
+        write_header(&mut file, &header).unwrap();
+
+        write(&mut file, &cipher, &mut header, 0, b"hello, world").unwrap();
+        // A write past the end leaves zeros in between
+        write(&mut file, &cipher, &mut header, 10_000, b"tail").unwrap();
+        assert_eq!(header.size, 10_004);
+        assert_eq!(read_header(&mut file), Ok(Some(header)));
+        // Two whole units, then 1812 bytes padded to 1824
+        assert_eq!(file.0.len(), HEADER_SIZE + 2 * UNIT_SIZE + 1824);
+        assert!(!file.0.windows(5).any(|window| window == b"hello"));
+
+        let mut buffer = vec![0xff; 10_100];
+        assert_eq!(read(&mut file, &cipher, &header, 0, &mut buffer), Ok(10_004));
+        assert_eq!(&buffer[..12], b"hello, world");
+        assert!(buffer[12..10_000].iter().all(|&byte| byte == 0));
+        assert_eq!(&buffer[10_000..10_004], b"tail");
+
+        // Overwrite across a unit boundary
+        write(&mut file, &cipher, &mut header, 4090, &[7; 12]).unwrap();
+        let mut middle = [0u8; 14];
+        assert_eq!(read(&mut file, &cipher, &header, 4089, &mut middle), Ok(14));
+        assert_eq!(middle, [0, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 0]);
+        assert_eq!(header.size, 10_004);
+
+        // Another file key reads garbage
+        let mut wrong = [0u8; 12];
+        read(&mut file, &Xts::new(&[5; 64]), &header, 0, &mut wrong).unwrap();
+        assert_ne!(&wrong, b"hello, world");
+    }
+
+    #[test]
+    fn test_short_files_are_corrupt() {
+        let cipher = Xts::new(&[4; 64]);
+        let mut header = FileHeader::new([2; NONCE_SIZE]);
+        let mut file = MemoryFile(Vec::new());
+        write_header(&mut file, &header).unwrap();
+        write(&mut file, &cipher, &mut header, 0, &[9; 5000]).unwrap();
+
+        file.0.truncate(HEADER_SIZE + UNIT_SIZE + 8);
+        let mut buffer = [0u8; 16];
+        assert_eq!(read(&mut file, &cipher, &header, 4096, &mut buffer), Err(ContentsError::Corrupt));
+        assert_eq!(read_header(&mut MemoryFile(vec![1; 10])), Err(ContentsError::Corrupt));
+        assert_eq!(read_header(&mut MemoryFile(vec![0; 64])), Err(ContentsError::Corrupt));
+    }
+}
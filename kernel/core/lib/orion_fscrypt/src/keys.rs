@@ -0,0 +1,81 @@
+/*
+ * Orion Operating System - File Encryption Keys
+ *
+ * The master key of a user is derived by the keyring from the key the
+ * session added there; every other key is derived from the master key
+ * with HKDF-SHA512, under a context byte and, for contents, the nonce of
+ * the file. Key material is erased when dropped.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::aes::{Aes256, Xts, AES256_KEY_SIZE, XTS_KEY_SIZE};
+use orion_crypto::kdf::hkdf_sha512;
+use orion_crypto::wipe;
+
+pub const MASTER_KEY_SIZE: usize = 64;
+pub const KEY_IDENTIFIER_SIZE: usize = 16;
+pub const NONCE_SIZE: usize = 16;
+
+pub type KeyIdentifier = [u8; KEY_IDENTIFIER_SIZE];
+pub type Nonce = [u8; NONCE_SIZE];
+
+/// Prefix of every HKDF info, followed by the context
+const INFO_PREFIX: &[u8] = b"orion-fscrypt";
+
+// Contexts
+const CONTEXT_IDENTIFIER: u8 = 1;
+const CONTEXT_NAMES: u8 = 2;
+const CONTEXT_CONTENTS: u8 = 3;
+
+pub struct MasterKey([u8; MASTER_KEY_SIZE]);
+
+impl MasterKey {
+    pub fn new(key: [u8; MASTER_KEY_SIZE]) -> Self {
+        Self(key)
+    }
+
+    fn derive(&self, context: u8, nonce: &[u8], out: &mut [u8]) {
+        let mut info = Vec::with_capacity(INFO_PREFIX.len() + 1 + nonce.len());
+        info.extend_from_slice(INFO_PREFIX);
+        info.push(context);
+        info.extend_from_slice(nonce);
+        hkdf_sha512(&[], &self.0, &info, out);
+    }
+
+    /// Identifier recorded in policies, which tells nothing of the key
+    pub fn identifier(&self) -> KeyIdentifier {
+        let mut identifier = [0u8; KEY_IDENTIFIER_SIZE];
+        self.derive(CONTEXT_IDENTIFIER, &[], &mut identifier);
+        identifier
+    }
+
+    pub fn names_cipher(&self) -> Aes256 {
+        let mut key = [0u8; AES256_KEY_SIZE];
+        self.derive(CONTEXT_NAMES, &[], &mut key);
+        let cipher = Aes256::new(&key);
+        wipe(&mut key);
+        cipher
+    }
+
+    /// Contents cipher of the file with `nonce`
+    pub fn contents_cipher(&self, nonce: &Nonce) -> Xts {
+        let mut key = [0u8; XTS_KEY_SIZE];
+        self.derive(CONTEXT_CONTENTS, nonce, &mut key);
+        let cipher = Xts::new(&key);
+        wipe(&mut key);
+        cipher
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
@@ -0,0 +1,42 @@
+/*
+ * Orion Operating System - File Encryption
+ *
+ * Encryption of user data directories, file by file, on top of whatever
+ * file system holds them, as fscrypt does. A directory gets a policy
+ * (policy.rs) naming the key of its user; every name below it is
+ * encrypted (names.rs), and every file carries a header with a nonce of
+ * its own, from which its contents key is derived (contents.rs). Keys
+ * come from the keyring when the user logs in and live in a table of the
+ * fs server (table.rs) until the session ends: once a key is removed the
+ * names and contents under its policy cannot be read anymore, not even
+ * by processes that had the files open.
+ *
+ * Keys, all derived with HKDF-SHA512 from the 64-byte master key
+ * (keys.rs):
+ *
+ *   identifier   16 bytes, recorded in the policy
+ *   names        AES-256-CBC, one per policy
+ *   contents     AES-256-XTS, one per file nonce
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod contents;
+pub mod keys;
+pub mod names;
+pub mod policy;
+pub mod table;
+
+pub use contents::{ContentsError, FileHeader, LowerFile, HEADER_SIZE, UNIT_SIZE};
+pub use keys::{KeyIdentifier, MasterKey, Nonce, KEY_IDENTIFIER_SIZE, MASTER_KEY_SIZE, NONCE_SIZE};
+pub use names::{decrypt_name, encrypt_name, NameError, MAX_NAME_LEN};
+pub use policy::{Policy, POLICY_FILE, POLICY_SIZE};
+pub use table::{KeyTable, Removal};
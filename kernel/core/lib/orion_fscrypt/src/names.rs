@@ -0,0 +1,139 @@
+/*
+ * Orion Operating System - File Name Encryption
+ *
+ * A name below an encrypted directory is padded with NULs to a multiple
+ * of 16 bytes, encrypted with AES-256-CBC under the names key of the
+ * policy and a zero IV, and stored in URL-safe base64 without padding.
+ * The same name always encrypts the same way, so a lookup encrypts the
+ * name asked for and finds it in the directory; the cost is that equal
+ * names in two directories of a policy are equal on disk too.
+ *
+ * Stored names must fit the 255 bytes of a directory entry, which leaves
+ * MAX_NAME_LEN bytes for the name itself. A stored name never starts with
+ * '.', so the policy file cannot be shadowed by an encrypted name.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_crypto::aes::{cbc_decrypt, cbc_encrypt, Aes256, AES_BLOCK_SIZE};
+
+/// Longest name below an encrypted directory; its stored form is 235 bytes
+pub const MAX_NAME_LEN: usize = 176;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+const IV: [u8; AES_BLOCK_SIZE] = [0; AES_BLOCK_SIZE];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameError {
+    /// Longer than MAX_NAME_LEN
+    TooLong,
+    /// Not a name: empty, "." or "..", or holding '/' or NUL. For stored
+    /// names, not one this key encrypted
+    Invalid,
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| bits | (*byte as u32) << (16 - 8 * index));
+        for index in 0..=chunk.len() {
+            out.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        let mut bits = 0u32;
+        for (index, character) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|symbol| symbol == character)? as u32;
+            bits |= value << (18 - 6 * index);
+        }
+        for index in 0..chunk.len() - 1 {
+            out.push((bits >> (16 - 8 * index)) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn is_valid(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.bytes().any(|byte| byte == b'/' || byte == 0)
+}
+
+/// Stored form of `name`, one component of a path
+pub fn encrypt_name(cipher: &Aes256, name: &str) -> Result<String, NameError> {
+    if !is_valid(name) {
+        return Err(NameError::Invalid);
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(NameError::TooLong);
+    }
+    let mut padded = vec![0u8; name.len().div_ceil(AES_BLOCK_SIZE).max(1) * AES_BLOCK_SIZE];
+    padded[..name.len()].copy_from_slice(name.as_bytes());
+    cbc_encrypt(cipher, &IV, &mut padded);
+    Ok(encode_base64(&padded))
+}
+
+/// Name a stored name stands for
+pub fn decrypt_name(cipher: &Aes256, stored: &str) -> Result<String, NameError> {
+    let mut data = decode_base64(stored).ok_or(NameError::Invalid)?;
+    // One stored form per name: unused trailing bits must be zero
+    if data.is_empty() || !data.len().is_multiple_of(AES_BLOCK_SIZE) || encode_base64(&data) != stored {
+        return Err(NameError::Invalid);
+    }
+    cbc_decrypt(cipher, &IV, &mut data);
+    let end = data.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+    // Padding only ever fills the last block
+    if data.len() - end >= AES_BLOCK_SIZE {
+        return Err(NameError::Invalid);
+    }
+    data.truncate(end);
+    let name = String::from_utf8(data).map_err(|_| NameError::Invalid)?;
+    if !is_valid(&name) || name.len() > MAX_NAME_LEN {
+        return Err(NameError::Invalid);
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        let cipher = Aes256::new(&[7; 32]);
+        for name in ["a", "notes.txt", "exactly sixteen!", "Photos d'été"] {
+            let stored = encrypt_name(&cipher, name).unwrap();
+            assert!(!stored.starts_with('.') && !stored.contains(name));
+            assert_eq!(decrypt_name(&cipher, &stored).unwrap(), name);
+        }
+        // Lookups find a name by encrypting it again
+        assert_eq!(encrypt_name(&cipher, "a"), encrypt_name(&cipher, "a"));
+
+        let longest = "x".repeat(MAX_NAME_LEN);
+        assert!(encrypt_name(&cipher, &longest).unwrap().len() <= 255);
+        assert_eq!(encrypt_name(&cipher, &(longest + "x")), Err(NameError::TooLong));
+        assert_eq!(encrypt_name(&cipher, ".."), Err(NameError::Invalid));
+        assert_eq!(encrypt_name(&cipher, "a/b"), Err(NameError::Invalid));
+
+        // Another key, or a name that was never encrypted, does not decrypt
+        let stored = encrypt_name(&cipher, "notes.txt").unwrap();
+        assert!(decrypt_name(&Aes256::new(&[8; 32]), &stored).is_err());
+        assert_eq!(decrypt_name(&cipher, "notes.txt"), Err(NameError::Invalid));
+        assert!(decrypt_name(&cipher, &"A".repeat(22)).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * Orion Operating System - Encryption Policies
+ *
+ * The policy of an encrypted directory lives in POLICY_FILE at its top,
+ * in the clear: it names the key by identifier and the modes, so the
+ * directory can be attached again after a restart and a wrong key is
+ * refused rather than producing garbage. All fields are little-endian:
+ *
+ *   0    4   magic "OFCP"
+ *   4    4   policy version, 1
+ *   8    1   contents mode, 1 for AES-256-XTS
+ *   9    1   names mode, 1 for AES-256-CBC
+ *   10   2   flags, none defined
+ *   12   16  key identifier
+ *   28   4   reserved
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::keys::{KeyIdentifier, KEY_IDENTIFIER_SIZE};
+
+/// File holding the policy at the top of an encrypted directory
+pub const POLICY_FILE: &str = ".orion-crypt";
+pub const POLICY_SIZE: usize = 32;
+
+const POLICY_MAGIC: &[u8; 4] = b"OFCP";
+const POLICY_VERSION: u32 = 1;
+const CONTENTS_AES_256_XTS: u8 = 1;
+const NAMES_AES_256_CBC: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    pub key_identifier: KeyIdentifier,
+}
+
+impl Policy {
+    pub fn encode(&self) -> [u8; POLICY_SIZE] {
+        let mut out = [0u8; POLICY_SIZE];
+        out[..4].copy_from_slice(POLICY_MAGIC);
+        out[4..8].copy_from_slice(&POLICY_VERSION.to_le_bytes());
+        out[8] = CONTENTS_AES_256_XTS;
+        out[9] = NAMES_AES_256_CBC;
+        out[12..28].copy_from_slice(&self.key_identifier);
+        out
+    }
+
+    /// None for anything but a policy of this version with known modes
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < POLICY_SIZE || &data[..4] != POLICY_MAGIC || data[4..8] != POLICY_VERSION.to_le_bytes() {
+            return None;
+        }
+        if data[8] != CONTENTS_AES_256_XTS || data[9] != NAMES_AES_256_CBC || data[10..12] != [0, 0] {
+            return None;
+        }
+        let mut key_identifier = [0u8; KEY_IDENTIFIER_SIZE];
+        key_identifier.copy_from_slice(&data[12..28]);
+        Some(Self { key_identifier })
+    }
+}
@@ -0,0 +1,162 @@
+/*
+ * Orion Operating System - File Encryption Key Table
+ *
+ * The keys the fs server holds, by identifier. Each key records the users
+ * who added it, so that one session logging out does not lock the files
+ * of another session of the same user; the key is removed with its last
+ * user. Contents ciphers are derived on first use and cached per file
+ * nonce; they go with the master key, so removing a key leaves nothing
+ * from which the data under its policy could be read.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use orion_crypto::aes::{Aes256, Xts};
+
+use crate::keys::{KeyIdentifier, MasterKey, Nonce};
+
+/// Contents ciphers cached per key
+const MAX_CACHED_FILES: usize = 256;
+
+struct Unlocked {
+    master: MasterKey,
+    names: Aes256,
+    users: BTreeSet<u64>,
+    files: BTreeMap<Nonce, Xts>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// The key is gone
+    Removed,
+    /// The user was dropped, and the key stays for this many others
+    OtherUsers(usize),
+}
+
+#[derive(Default)]
+pub struct KeyTable {
+    keys: BTreeMap<KeyIdentifier, Unlocked>,
+}
+
+impl KeyTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a master key for `user`. Adding a key already present only
+    /// records the user
+    pub fn add(&mut self, master: MasterKey, user: u64) -> KeyIdentifier {
+        let identifier = master.identifier();
+        let unlocked = self.keys.entry(identifier).or_insert_with(|| Unlocked {
+            names: master.names_cipher(),
+            master,
+            users: BTreeSet::new(),
+            files: BTreeMap::new(),
+        });
+        unlocked.users.insert(user);
+        identifier
+    }
+
+    /// Drop `user` from the key, and the key with its last user. None if
+    /// the key is absent or `user` never added it
+    pub fn remove(&mut self, identifier: &KeyIdentifier, user: u64) -> Option<Removal> {
+        let unlocked = self.keys.get_mut(identifier)?;
+        if !unlocked.users.remove(&user) {
+            return None;
+        }
+        if !unlocked.users.is_empty() {
+            return Some(Removal::OtherUsers(unlocked.users.len()));
+        }
+        self.keys.remove(identifier);
+        Some(Removal::Removed)
+    }
+
+    /// Remove the key whoever added it
+    pub fn remove_all(&mut self, identifier: &KeyIdentifier) -> bool {
+        self.keys.remove(identifier).is_some()
+    }
+
+    pub fn contains(&self, identifier: &KeyIdentifier) -> bool {
+        self.keys.contains_key(identifier)
+    }
+
+    /// Number of users holding the key, 0 if absent
+    pub fn users(&self, identifier: &KeyIdentifier) -> usize {
+        self.keys.get(identifier).map_or(0, |unlocked| unlocked.users.len())
+    }
+
+    pub fn names(&self, identifier: &KeyIdentifier) -> Option<&Aes256> {
+        self.keys.get(identifier).map(|unlocked| &unlocked.names)
+    }
+
+    /// Contents cipher of the file with `nonce` under the key
+    pub fn contents(&mut self, identifier: &KeyIdentifier, nonce: &Nonce) -> Option<&Xts> {
+        let unlocked = self.keys.get_mut(identifier)?;
+        if !unlocked.files.contains_key(nonce) {
+            if unlocked.files.len() >= MAX_CACHED_FILES {
+                unlocked.files.pop_first();
+            }
+            let cipher = unlocked.master.contents_cipher(nonce);
+            unlocked.files.insert(*nonce, cipher);
+        }
+        unlocked.files.get(nonce)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::names::{decrypt_name, encrypt_name};
+    use crate::policy::Policy;
+
+    #[test]
+    fn test_keys_go_with_their_last_user() {
+        let mut table = KeyTable::new();
+        let identifier = table.add(MasterKey::new([3; 64]), 1000);
+        assert_eq!(table.add(MasterKey::new([3; 64]), 1001), identifier);
+        assert_eq!(table.users(&identifier), 2);
+        assert!(table.contents(&identifier, &[9; 16]).is_some());
+
+        let stored = encrypt_name(table.names(&identifier).unwrap(), "notes.txt").unwrap();
+        assert_eq!(decrypt_name(table.names(&identifier).unwrap(), &stored).as_deref(), Ok("notes.txt"));
+
+        assert_eq!(table.remove(&identifier, 2000), None);
+        assert_eq!(table.remove(&identifier, 1000), Some(Removal::OtherUsers(1)));
+        assert!(table.contains(&identifier));
+        assert_eq!(table.remove(&identifier, 1001), Some(Removal::Removed));
+        assert!(table.is_empty());
+        assert!(table.names(&identifier).is_none());
+        assert!(table.contents(&identifier, &[9; 16]).is_none());
+
+        let other = table.add(MasterKey::new([4; 64]), 1000);
+        assert_ne!(other, identifier);
+        assert!(table.remove_all(&other));
+        assert!(!table.remove_all(&other));
+    }
+
+    #[test]
+    fn test_policies_round_trip() {
+        let policy = Policy { key_identifier: MasterKey::new([3; 64]).identifier() };
+        let encoded = policy.encode();
+        assert_eq!(Policy::decode(&encoded), Some(policy));
+
+        let mut unknown_mode = encoded;
+        unknown_mode[8] = 2;
+        assert_eq!(Policy::decode(&unknown_mode), None);
+        assert_eq!(Policy::decode(&encoded[..16]), None);
+    }
+}
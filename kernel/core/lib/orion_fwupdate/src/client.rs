@@ -0,0 +1,192 @@
+/*
+ * Orion Operating System - Firmware Update Client
+ *
+ * Client side of the firmware control requests, used by the
+ * orion-fwupdate tool. The transport is a trait so the tool can call
+ * through the driver's IPC channel and tests straight into
+ * handle_control.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::control::{
+    read_u32, status_error, CTRL_ABORT, CTRL_ACTIVATE, CTRL_BEGIN, CTRL_CONFIRM, CTRL_QUERY, CTRL_READ, CTRL_ROLLBACK,
+    CTRL_STAGE, CTRL_VERIFY, MAX_READ, QUERY_SIZE, STATUS_OK,
+};
+use crate::image::VERSION_SIZE;
+use crate::updater::UpdateState;
+use crate::FwError;
+
+/// Carries a control request to a driver and returns its reply
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareStatus {
+    pub state: UpdateState,
+    pub active_slot: u8,
+    pub staging_slot: u8,
+    pub can_rollback: bool,
+    pub max_size: u32,
+    pub version: [u8; VERSION_SIZE],
+    /// Bytes of the image staged so far
+    pub received: u32,
+}
+
+pub struct FirmwareClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> FirmwareClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, opcode: u32, argument: &[u8]) -> Result<Vec<u8>, FwError> {
+        let mut request = Vec::with_capacity(4 + argument.len());
+        request.extend_from_slice(&opcode.to_le_bytes());
+        request.extend_from_slice(argument);
+        let response = self.transport.call(&request).ok_or(FwError::Device)?;
+        let status = read_u32(&response, 0).ok_or(FwError::Device)? as i32;
+        if status != STATUS_OK {
+            return Err(status_error(status));
+        }
+        Ok(response[4..].to_vec())
+    }
+
+    pub fn query(&mut self) -> Result<FirmwareStatus, FwError> {
+        let payload = self.call(CTRL_QUERY, &[])?;
+        if payload.len() < QUERY_SIZE {
+            return Err(FwError::Device);
+        }
+        let mut version = [0u8; VERSION_SIZE];
+        version.copy_from_slice(&payload[12..12 + VERSION_SIZE]);
+        Ok(FirmwareStatus {
+            state: read_u32(&payload, 0).and_then(UpdateState::from_u32).ok_or(FwError::Device)?,
+            active_slot: payload[4],
+            staging_slot: payload[5],
+            can_rollback: payload[6] != 0,
+            max_size: read_u32(&payload, 8).ok_or(FwError::Device)?,
+            version,
+            received: read_u32(&payload, 28).ok_or(FwError::Device)?,
+        })
+    }
+
+    /// Stage `image` in pieces of `chunk` bytes and have the driver verify
+    /// it; returns the version it carries. A failed transfer is aborted.
+    pub fn stage(&mut self, image: &[u8], chunk: usize) -> Result<[u8; VERSION_SIZE], FwError> {
+        if image.len() > u32::MAX as usize || chunk == 0 {
+            return Err(FwError::InvalidArgument);
+        }
+        self.call(CTRL_BEGIN, &(image.len() as u32).to_le_bytes())?;
+        for (index, piece) in image.chunks(chunk).enumerate() {
+            let mut argument = Vec::with_capacity(4 + piece.len());
+            argument.extend_from_slice(&((index * chunk) as u32).to_le_bytes());
+            argument.extend_from_slice(piece);
+            if let Err(error) = self.call(CTRL_STAGE, &argument) {
+                let _ = self.call(CTRL_ABORT, &[]);
+                return Err(error);
+            }
+        }
+        let payload = self.call(CTRL_VERIFY, &[])?;
+        let mut version = [0u8; VERSION_SIZE];
+        version.copy_from_slice(payload.get(..VERSION_SIZE).ok_or(FwError::Device)?);
+        Ok(version)
+    }
+
+    /// Run the verified image; it must be confirmed within `confirm_ms`
+    pub fn activate(&mut self, confirm_ms: u32) -> Result<u8, FwError> {
+        let payload = self.call(CTRL_ACTIVATE, &confirm_ms.to_le_bytes())?;
+        payload.first().copied().ok_or(FwError::Device)
+    }
+
+    pub fn confirm(&mut self) -> Result<(), FwError> {
+        self.call(CTRL_CONFIRM, &[]).map(|_| ())
+    }
+
+    pub fn rollback(&mut self) -> Result<(), FwError> {
+        self.call(CTRL_ROLLBACK, &[]).map(|_| ())
+    }
+
+    pub fn abort(&mut self) -> Result<(), FwError> {
+        self.call(CTRL_ABORT, &[]).map(|_| ())
+    }
+
+    /// The whole running image, read `MAX_READ` bytes at a time; `limit`
+    /// bounds its size against a device that never reports the end
+    pub fn read_image(&mut self, limit: u32) -> Result<Vec<u8>, FwError> {
+        let mut image = Vec::new();
+        while (image.len() as u32) < limit {
+            let length = MAX_READ.min(limit - image.len() as u32);
+            let mut argument = Vec::with_capacity(8);
+            argument.extend_from_slice(&(image.len() as u32).to_le_bytes());
+            argument.extend_from_slice(&length.to_le_bytes());
+            let piece = self.call(CTRL_READ, &argument)?;
+            if piece.len() > length as usize {
+                return Err(FwError::Device);
+            }
+            image.extend_from_slice(&piece);
+            if piece.len() < length as usize {
+                break;
+            }
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::handle_control;
+    use crate::image::tests::{header, SECRET, VERSION};
+    use crate::image::{self, ANY_DEVICE};
+    use crate::updater::tests::{updater, FakeDevice};
+    use crate::updater::FirmwareUpdater;
+
+    struct Local {
+        updater: FirmwareUpdater,
+        device: FakeDevice,
+    }
+
+    impl Transport for &mut Local {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            Some(handle_control(&mut self.updater, &mut self.device, request, 0))
+        }
+    }
+
+    #[test]
+    fn test_update_through_the_client() {
+        let mut local = Local { updater: updater(), device: FakeDevice::new() };
+        let image = image::build(&header(0x144d, ANY_DEVICE), &[0x42; 700], &SECRET);
+        let mut client = FirmwareClient::new(&mut local);
+
+        assert_eq!(client.stage(&image, 256), Ok(VERSION));
+        assert_eq!(client.activate(1000), Ok(2));
+        let status = client.query().unwrap();
+        assert_eq!((status.state, status.active_slot, status.can_rollback), (UpdateState::Activated, 2, true));
+        assert_eq!(client.confirm(), Ok(()));
+        assert_eq!(client.rollback(), Err(FwError::NotFound));
+        assert_eq!(local.device.slots[1], [0x42; 700]);
+    }
+
+    #[test]
+    fn test_reads_the_image_in_pieces() {
+        let mut device = FakeDevice::new();
+        device.slots[0] = (0..MAX_READ + 10).map(|byte| byte as u8).collect();
+        let mut local = Local { updater: FirmwareUpdater::new(), device };
+        let mut client = FirmwareClient::new(&mut local);
+
+        let image = client.read_image(u32::MAX).unwrap();
+        assert_eq!(image, local.device.slots[0]);
+        let mut client = FirmwareClient::new(&mut local);
+        assert_eq!(client.read_image(5), Ok(alloc::vec![0, 1, 2, 3, 4]));
+    }
+}
@@ -0,0 +1,224 @@
+/*
+ * Orion Operating System - Firmware Update Control
+ *
+ * Control requests a driver accepts on its IPC channel as an ioctl of
+ * length FW_IOCTL_CONTROL. All fields are little-endian; every request
+ * starts with a 32-bit opcode and every reply with a 32-bit signed status
+ * (0 or a negative errno).
+ *
+ *   QUERY                            -> state:u32 active_slot:u8
+ *                                       staging_slot:u8 can_rollback:u8
+ *                                       reserved:u8 max_size:u32
+ *                                       version[16] received:u32
+ *   BEGIN       size:u32             ->
+ *   STAGE       offset:u32 data      ->
+ *   VERIFY                           -> version[16]
+ *   ACTIVATE    confirm_ms:u32       -> slot:u8
+ *   CONFIRM                          ->
+ *   ROLLBACK                         ->
+ *   ABORT                            ->
+ *   READ        offset:u32 length:u32 -> data
+ *
+ * READ returns the running image as the device holds it, at most
+ * MAX_READ bytes per request and fewer at the end of the image.
+ *
+ * Trust anchors cannot be set through these requests: the driver fetches
+ * them from the I/O server when it starts (FirmwareUpdater::load_trust_anchors),
+ * so a client of the driver's channel cannot slip in its own key.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_proto::reply;
+
+use crate::updater::FirmwareUpdater;
+use crate::{FirmwareDevice, FwError};
+
+/// Ioctl length selecting firmware control requests
+pub const FW_IOCTL_CONTROL: u64 = 0x3010;
+
+// Opcodes
+pub const CTRL_QUERY: u32 = 1;
+pub const CTRL_BEGIN: u32 = 2;
+pub const CTRL_STAGE: u32 = 3;
+pub const CTRL_VERIFY: u32 = 4;
+pub const CTRL_ACTIVATE: u32 = 5;
+pub const CTRL_CONFIRM: u32 = 6;
+pub const CTRL_ROLLBACK: u32 = 7;
+pub const CTRL_ABORT: u32 = 8;
+pub const CTRL_READ: u32 = 10;
+
+/// I/O server request returning its locked trust anchors as
+/// count:u32 {public_key[32]} (services/io/src/protocol.rs)
+pub const IO_OP_TRUST_ANCHORS: u32 = 9;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_ENODEV: i32 = -19;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_EFBIG: i32 = -27;
+pub const STATUS_EOPNOTSUPP: i32 = -95;
+pub const STATUS_EKEYREJECTED: i32 = -129;
+
+/// Size of the QUERY payload
+pub const QUERY_SIZE: usize = 32;
+
+/// Largest READ reply payload
+pub const MAX_READ: u32 = 16 * 1024;
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn error_status(error: FwError) -> i32 {
+    match error {
+        FwError::InvalidArgument | FwError::Malformed => STATUS_EINVAL,
+        FwError::Busy => STATUS_EBUSY,
+        FwError::NotFound => STATUS_ENOENT,
+        FwError::TooLarge => STATUS_EFBIG,
+        FwError::Rejected => STATUS_EKEYREJECTED,
+        FwError::WrongDevice => STATUS_ENODEV,
+        FwError::Locked | FwError::NoRollback => STATUS_EPERM,
+        FwError::Unsupported => STATUS_EOPNOTSUPP,
+        FwError::Device => STATUS_EIO,
+    }
+}
+
+pub fn status_error(status: i32) -> FwError {
+    match status {
+        STATUS_EINVAL => FwError::InvalidArgument,
+        STATUS_EBUSY => FwError::Busy,
+        STATUS_ENOENT => FwError::NotFound,
+        STATUS_EFBIG => FwError::TooLarge,
+        STATUS_EKEYREJECTED => FwError::Rejected,
+        STATUS_ENODEV => FwError::WrongDevice,
+        STATUS_EPERM => FwError::Locked,
+        STATUS_EOPNOTSUPP => FwError::Unsupported,
+        _ => FwError::Device,
+    }
+}
+
+fn result_reply(result: Result<Vec<u8>, FwError>) -> Vec<u8> {
+    match result {
+        Ok(payload) => reply(STATUS_OK, &payload),
+        Err(error) => reply(error_status(error), &[]),
+    }
+}
+
+/// Serve one control request; `now` is the monotonic clock in nanoseconds
+pub fn handle_control(
+    updater: &mut FirmwareUpdater,
+    device: &mut dyn FirmwareDevice,
+    request: &[u8],
+    now: u64,
+) -> Vec<u8> {
+    let Some(opcode) = read_u32(request, 0) else {
+        return reply(STATUS_EINVAL, &[]);
+    };
+    let result = match opcode {
+        CTRL_QUERY => device.firmware_info().map(|info| {
+            let mut out = Vec::with_capacity(QUERY_SIZE);
+            out.extend_from_slice(&updater.state().as_u32().to_le_bytes());
+            out.extend_from_slice(&[info.active_slot, info.staging_slot, info.can_rollback as u8, 0]);
+            out.extend_from_slice(&info.max_size.to_le_bytes());
+            out.extend_from_slice(&info.version);
+            out.extend_from_slice(&(updater.received() as u32).to_le_bytes());
+            out
+        }),
+        CTRL_BEGIN => match read_u32(request, 4) {
+            Some(size) => device.firmware_info().and_then(|info| updater.begin(size as usize, info.max_size)),
+            None => Err(FwError::InvalidArgument),
+        }
+        .map(|_| Vec::new()),
+        CTRL_STAGE => match read_u32(request, 4) {
+            Some(offset) => updater.stage(offset as usize, &request[8..]),
+            None => Err(FwError::InvalidArgument),
+        }
+        .map(|_| Vec::new()),
+        CTRL_VERIFY => updater.verify(device).map(|header| header.version.to_vec()),
+        CTRL_ACTIVATE => match read_u32(request, 4) {
+            Some(confirm_ms) => updater.activate(device, now, confirm_ms as u64 * 1_000_000),
+            None => Err(FwError::InvalidArgument),
+        }
+        .map(|slot| alloc::vec![slot]),
+        CTRL_CONFIRM => updater.confirm().map(|_| Vec::new()),
+        CTRL_ROLLBACK => updater.rollback(device).map(|_| Vec::new()),
+        CTRL_ABORT => updater.abort().map(|_| Vec::new()),
+        CTRL_READ => match (read_u32(request, 4), read_u32(request, 8)) {
+            (Some(offset), Some(length)) => device.read_image(offset, length.min(MAX_READ)),
+            _ => Err(FwError::InvalidArgument),
+        },
+        _ => Err(FwError::Unsupported),
+    };
+    result_reply(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::tests::{header, SECRET};
+    use crate::image::{self, ANY_DEVICE};
+    use crate::updater::tests::{updater, FakeDevice};
+
+    fn request(opcode: u32, argument: &[u8]) -> Vec<u8> {
+        let mut out = opcode.to_le_bytes().to_vec();
+        out.extend_from_slice(argument);
+        out
+    }
+
+    fn status(reply: &[u8]) -> i32 {
+        read_u32(reply, 0).unwrap() as i32
+    }
+
+    fn stage(updater: &mut FirmwareUpdater, device: &mut FakeDevice, image: &[u8]) -> Vec<u8> {
+        handle_control(updater, device, &request(CTRL_BEGIN, &(image.len() as u32).to_le_bytes()), 0);
+        handle_control(updater, device, &request(CTRL_STAGE, &[&0u32.to_le_bytes()[..], image].concat()), 0);
+        handle_control(updater, device, &request(CTRL_VERIFY, &[]), 0)
+    }
+
+    #[test]
+    fn test_update_over_control_requests() {
+        let mut device = FakeDevice::new();
+        let image = image::build(&header(0x144d, ANY_DEVICE), &[0x42; 300], &SECRET);
+
+        // Without trust anchors nothing verifies
+        let mut bare = FirmwareUpdater::new();
+        assert_eq!(status(&stage(&mut bare, &mut device, &image)), STATUS_EKEYREJECTED);
+
+        let mut updater = updater();
+        let verified = stage(&mut updater, &mut device, &image);
+        assert_eq!((status(&verified), &verified[4..9]), (STATUS_OK, &b"2.1.0"[..]));
+        let activate = request(CTRL_ACTIVATE, &5000u32.to_le_bytes());
+        assert_eq!(handle_control(&mut updater, &mut device, &activate, 0), reply(STATUS_OK, &[2]));
+
+        let query = handle_control(&mut updater, &mut device, &request(CTRL_QUERY, &[]), 0);
+        assert_eq!(query.len(), 4 + QUERY_SIZE);
+        assert_eq!((read_u32(&query, 4), query[8]), (Some(3), 2));
+        assert_eq!(status(&handle_control(&mut updater, &mut device, &request(CTRL_ROLLBACK, &[]), 0)), STATUS_OK);
+        assert_eq!(status(&handle_control(&mut updater, &mut device, &request(42, &[]), 0)), STATUS_EOPNOTSUPP);
+    }
+
+    #[test]
+    fn test_reads_the_running_image() {
+        let mut updater = FirmwareUpdater::new();
+        let mut device = FakeDevice::new();
+        let mut read = |argument: &[u8]| handle_control(&mut updater, &mut device, &request(CTRL_READ, argument), 0);
+        let range = |offset: u32, length: u32| [offset.to_le_bytes(), length.to_le_bytes()].concat();
+
+        assert_eq!(read(&range(1, 100)), reply(STATUS_OK, b"ld"));
+        assert_eq!(read(&range(3, 1)), reply(STATUS_OK, &[]));
+        assert_eq!(status(&read(&range(9, 1))), STATUS_EINVAL);
+        assert_eq!(status(&read(&0u32.to_le_bytes())), STATUS_EINVAL);
+    }
+}
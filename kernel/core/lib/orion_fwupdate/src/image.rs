@@ -0,0 +1,173 @@
+/*
+ * Orion Operating System - Firmware Images
+ *
+ * A firmware image wraps the vendor payload in a header naming the device
+ * it is built for, and ends with the same signature trailer as driver
+ * images (services/io/src/signing.rs), so both are signed with the same
+ * tooling and checked against the same trust anchors. All fields are
+ * little-endian:
+ *
+ *   0   4  magic "OFWU"
+ *   4   4  format version
+ *   8   2  PCI vendor id
+ *   10  2  PCI device id, 0xFFFF for any device of the vendor
+ *   12  4  payload size
+ *   16  16 firmware version, ASCII padded with NULs
+ *   32  32 reserved
+ *   64     payload
+ *          trailer: Ed25519 signature over header and payload (64),
+ *          key id (8), trailer version (4), magic "ORIONSIG" (8)
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{self, PUBLIC_KEY_SIZE, SECRET_KEY_SIZE, SIGNATURE_SIZE};
+use orion_crypto::sha512::Sha512;
+
+use crate::FwError;
+
+pub const IMAGE_MAGIC: &[u8; 4] = b"OFWU";
+pub const IMAGE_FORMAT: u32 = 1;
+pub const HEADER_SIZE: usize = 64;
+pub const VERSION_SIZE: usize = 16;
+
+/// Device id of images that fit every device of their vendor
+pub const ANY_DEVICE: u16 = 0xFFFF;
+
+pub const SIGNATURE_MAGIC: [u8; 8] = *b"ORIONSIG";
+pub const TRAILER_VERSION: u32 = 1;
+pub const TRAILER_SIZE: usize = SIGNATURE_SIZE + 8 + 4 + 8;
+
+pub type KeyId = [u8; 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub payload_size: u32,
+    pub version: [u8; VERSION_SIZE],
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+impl ImageHeader {
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut out = [0u8; HEADER_SIZE];
+        out[..4].copy_from_slice(IMAGE_MAGIC);
+        out[4..8].copy_from_slice(&IMAGE_FORMAT.to_le_bytes());
+        out[8..10].copy_from_slice(&self.vendor_id.to_le_bytes());
+        out[10..12].copy_from_slice(&self.device_id.to_le_bytes());
+        out[12..16].copy_from_slice(&self.payload_size.to_le_bytes());
+        out[16..32].copy_from_slice(&self.version);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, FwError> {
+        if data.len() < HEADER_SIZE || &data[..4] != IMAGE_MAGIC || read_u32(data, 4) != IMAGE_FORMAT {
+            return Err(FwError::Malformed);
+        }
+        let mut version = [0u8; VERSION_SIZE];
+        version.copy_from_slice(&data[16..32]);
+        Ok(Self {
+            vendor_id: read_u16(data, 8),
+            device_id: read_u16(data, 10),
+            payload_size: read_u32(data, 12),
+            version,
+        })
+    }
+
+    /// Whether the image is meant for a device with these PCI ids
+    pub fn matches(&self, vendor_id: u16, device_id: u16) -> bool {
+        self.vendor_id == vendor_id && (self.device_id == ANY_DEVICE || self.device_id == device_id)
+    }
+}
+
+/// Identifier of a public key as recorded in signature trailers
+pub fn key_id(public_key: &[u8; PUBLIC_KEY_SIZE]) -> KeyId {
+    let digest = Sha512::digest(public_key);
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&digest[..8]);
+    id
+}
+
+/// Check a complete image against `keys` and return its header and payload
+pub fn verify<'a>(image: &'a [u8], keys: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<(ImageHeader, &'a [u8]), FwError> {
+    if image.len() < HEADER_SIZE + TRAILER_SIZE {
+        return Err(FwError::Malformed);
+    }
+    let (signed, trailer) = image.split_at(image.len() - TRAILER_SIZE);
+    if trailer[TRAILER_SIZE - 8..] != SIGNATURE_MAGIC {
+        return Err(FwError::Rejected);
+    }
+    if read_u32(trailer, SIGNATURE_SIZE + 8) != TRAILER_VERSION {
+        return Err(FwError::Malformed);
+    }
+
+    let header = ImageHeader::decode(signed)?;
+    if header.payload_size as usize != signed.len() - HEADER_SIZE {
+        return Err(FwError::Malformed);
+    }
+
+    let mut signature = [0u8; SIGNATURE_SIZE];
+    signature.copy_from_slice(&trailer[..SIGNATURE_SIZE]);
+    let id = &trailer[SIGNATURE_SIZE..SIGNATURE_SIZE + 8];
+    let key = keys.iter().find(|key| key_id(key) == id).ok_or(FwError::Rejected)?;
+    if !ed25519::verify(key, signed, &signature) {
+        return Err(FwError::Rejected);
+    }
+    Ok((header, &signed[HEADER_SIZE..]))
+}
+
+/// Build a signed image, as the release tooling does with the keyring
+pub fn build(header: &ImageHeader, payload: &[u8], secret_key: &[u8; SECRET_KEY_SIZE]) -> Vec<u8> {
+    let mut image = Vec::with_capacity(HEADER_SIZE + payload.len() + TRAILER_SIZE);
+    image.extend_from_slice(&ImageHeader { payload_size: payload.len() as u32, ..*header }.encode());
+    image.extend_from_slice(payload);
+    let signature = ed25519::sign(secret_key, &image);
+    image.extend_from_slice(&signature);
+    image.extend_from_slice(&key_id(&ed25519::public_key(secret_key)));
+    image.extend_from_slice(&TRAILER_VERSION.to_le_bytes());
+    image.extend_from_slice(&SIGNATURE_MAGIC);
+    image
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub const SECRET: [u8; SECRET_KEY_SIZE] = [7; SECRET_KEY_SIZE];
+    pub const VERSION: [u8; VERSION_SIZE] = *b"2.1.0\0\0\0\0\0\0\0\0\0\0\0";
+
+    pub fn header(vendor_id: u16, device_id: u16) -> ImageHeader {
+        ImageHeader { vendor_id, device_id, payload_size: 0, version: VERSION }
+    }
+
+    #[test]
+    fn test_signed_images_verify() {
+        let keys = [ed25519::public_key(&SECRET)];
+        let image = build(&header(0x8086, ANY_DEVICE), &[0xA5; 200], &SECRET);
+        let (parsed, payload) = verify(&image, &keys).unwrap();
+        assert_eq!(payload, &[0xA5; 200]);
+        assert!(parsed.matches(0x8086, 0x10d3));
+        assert!(!parsed.matches(0x144d, 0x10d3));
+
+        // Any change to the signed part, and unknown keys, are refused
+        let mut tampered = image.clone();
+        tampered[HEADER_SIZE + 3] ^= 1;
+        assert_eq!(verify(&tampered, &keys), Err(FwError::Rejected));
+        assert_eq!(verify(&image, &[ed25519::public_key(&[9; SECRET_KEY_SIZE])]), Err(FwError::Rejected));
+        assert_eq!(verify(&image[..image.len() - TRAILER_SIZE], &keys), Err(FwError::Rejected));
+    }
+}
@@ -0,0 +1,100 @@
+/*
+ * Orion Operating System - Firmware Update
+ *
+ * Generic firmware update for device drivers. An update goes through the
+ * same steps on every device: the image is staged in the driver, its
+ * signature is checked against the trust anchors the driver took from
+ * the I/O server when it started, and only then is it written to a firmware
+ * slot and activated. An activated image has to be confirmed before a
+ * deadline; otherwise, or on request, the driver goes back to the image
+ * that ran before.
+ *
+ * Drivers implement FirmwareDevice for their hardware (NVMe firmware
+ * slots, e1000e NVM) and feed control requests to handle_control; the
+ * orion-fwupdate tool drives them through FirmwareClient.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+pub mod client;
+pub mod control;
+pub mod image;
+pub mod updater;
+
+pub use client::{FirmwareClient, FirmwareStatus, Transport};
+pub use control::{handle_control, FW_IOCTL_CONTROL};
+pub use image::{ImageHeader, VERSION_SIZE};
+pub use updater::{FirmwareUpdater, UpdateState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FwError {
+    InvalidArgument,
+    /// Request does not fit the current step of the update
+    Busy,
+    /// Nothing to confirm, roll back or verify
+    NotFound,
+    /// Image larger than the device accepts
+    TooLarge,
+    /// Malformed image
+    Malformed,
+    /// Unsigned, signed by an unknown key or bad signature
+    Rejected,
+    /// Image built for another device
+    WrongDevice,
+    /// Trust anchors can no longer change
+    Locked,
+    /// The device cannot go back to its previous image
+    NoRollback,
+    Unsupported,
+    /// The device failed the operation
+    Device,
+}
+
+/// Firmware state reported by a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    /// Running version, ASCII padded with NULs
+    pub version: [u8; VERSION_SIZE],
+    pub active_slot: u8,
+    /// Slot a new image is written to
+    pub staging_slot: u8,
+    /// The image running before an activation can be brought back
+    pub can_rollback: bool,
+    /// Largest image payload the device takes
+    pub max_size: u32,
+}
+
+/// Firmware operations of one device
+pub trait FirmwareDevice {
+    /// PCI vendor and device id, matched against the image header
+    fn identity(&self) -> (u16, u16);
+
+    fn firmware_info(&mut self) -> Result<FirmwareInfo, FwError>;
+
+    /// Write `payload` to `slot` without running it
+    fn write_slot(&mut self, slot: u8, payload: &[u8]) -> Result<(), FwError>;
+
+    /// Run the image in `slot`
+    fn activate_slot(&mut self, slot: u8) -> Result<(), FwError>;
+
+    /// Go back to the image that ran from `slot` before the last activation
+    fn restore_slot(&mut self, slot: u8) -> Result<(), FwError> {
+        self.activate_slot(slot)
+    }
+
+    /// Up to `length` bytes of the running image from `offset`, fewer at
+    /// its end; devices whose firmware cannot be read back refuse
+    fn read_image(&mut self, _offset: u32, _length: u32) -> Result<Vec<u8>, FwError> {
+        Err(FwError::Unsupported)
+    }
+}
@@ -0,0 +1,398 @@
+/*
+ * Orion Operating System - Firmware Updater
+ *
+ * Driver side of an update. The steps only go forward:
+ *
+ *   Idle -> Receiving (BEGIN, STAGE...) -> Verified (VERIFY)
+ *        -> Activated (ACTIVATE) -> Idle (CONFIRM or ROLLBACK)
+ *
+ * Nothing reaches the device before the image is verified. Activation
+ * writes the payload to the device's staging slot and runs it; if that
+ * fails the previous image is brought back at once, and if the update is
+ * not confirmed before its deadline `tick` brings it back too, so a
+ * firmware that breaks the management path cannot strand the device.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::PUBLIC_KEY_SIZE;
+
+use crate::client::Transport;
+use crate::control::{read_u32, status_error, IO_OP_TRUST_ANCHORS, STATUS_OK};
+use crate::image::{self, ImageHeader, HEADER_SIZE, TRAILER_SIZE};
+use crate::{FirmwareDevice, FwError};
+
+/// Maximum number of trust anchors a driver accepts
+pub const MAX_KEYS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateState {
+    Idle,
+    Receiving,
+    Verified,
+    Activated,
+}
+
+impl UpdateState {
+    pub fn as_u32(self) -> u32 {
+        match self {
+            UpdateState::Idle => 0,
+            UpdateState::Receiving => 1,
+            UpdateState::Verified => 2,
+            UpdateState::Activated => 3,
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(UpdateState::Idle),
+            1 => Some(UpdateState::Receiving),
+            2 => Some(UpdateState::Verified),
+            3 => Some(UpdateState::Activated),
+            _ => None,
+        }
+    }
+}
+
+enum Step {
+    Idle,
+    Receiving { image: Vec<u8>, size: usize },
+    Verified { image: Vec<u8> },
+    Activated { previous_slot: u8, can_rollback: bool, deadline: u64 },
+}
+
+pub struct FirmwareUpdater {
+    keys: Vec<[u8; PUBLIC_KEY_SIZE]>,
+    locked: bool,
+    step: Step,
+}
+
+impl Default for FirmwareUpdater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FirmwareUpdater {
+    pub fn new() -> Self {
+        Self { keys: Vec::new(), locked: false, step: Step::Idle }
+    }
+
+    pub fn state(&self) -> UpdateState {
+        match self.step {
+            Step::Idle => UpdateState::Idle,
+            Step::Receiving { .. } => UpdateState::Receiving,
+            Step::Verified { .. } => UpdateState::Verified,
+            Step::Activated { .. } => UpdateState::Activated,
+        }
+    }
+
+    /// Bytes of the image received so far
+    pub fn received(&self) -> usize {
+        match &self.step {
+            Step::Receiving { image, .. } | Step::Verified { image, .. } => image.len(),
+            _ => 0,
+        }
+    }
+
+    /// Take the trust anchors of the I/O server and lock them. Returns the
+    /// number of keys; without any, every image is rejected.
+    pub fn load_trust_anchors(&mut self, io_server: &mut dyn Transport) -> Result<usize, FwError> {
+        let reply = io_server.call(&IO_OP_TRUST_ANCHORS.to_le_bytes()).ok_or(FwError::Device)?;
+        let status = read_u32(&reply, 0).ok_or(FwError::Device)? as i32;
+        if status != STATUS_OK {
+            return Err(status_error(status));
+        }
+        let count = read_u32(&reply, 4).ok_or(FwError::Device)? as usize;
+        let keys = reply.get(8..8 + count * PUBLIC_KEY_SIZE).ok_or(FwError::Device)?;
+        for key in keys.chunks(PUBLIC_KEY_SIZE) {
+            self.enroll_key(key)?;
+        }
+        self.lock_keys();
+        Ok(count)
+    }
+
+    /// Add a trust anchor; only until the set is locked
+    pub fn enroll_key(&mut self, public_key: &[u8]) -> Result<(), FwError> {
+        if self.locked {
+            return Err(FwError::Locked);
+        }
+        let public_key: [u8; PUBLIC_KEY_SIZE] = public_key.try_into().map_err(|_| FwError::InvalidArgument)?;
+        if self.keys.contains(&public_key) {
+            return Ok(());
+        }
+        if self.keys.len() >= MAX_KEYS {
+            return Err(FwError::TooLarge);
+        }
+        self.keys.push(public_key);
+        Ok(())
+    }
+
+    /// Freeze the trust anchors until the driver restarts
+    pub fn lock_keys(&mut self) {
+        self.locked = true;
+    }
+
+    /// Start receiving an image of `size` bytes, signature included
+    pub fn begin(&mut self, size: usize, max_payload: u32) -> Result<(), FwError> {
+        if matches!(self.step, Step::Activated { .. }) {
+            return Err(FwError::Busy);
+        }
+        if size < HEADER_SIZE + TRAILER_SIZE {
+            return Err(FwError::InvalidArgument);
+        }
+        if size - HEADER_SIZE - TRAILER_SIZE > max_payload as usize {
+            return Err(FwError::TooLarge);
+        }
+        self.step = Step::Receiving { image: Vec::with_capacity(size), size };
+        Ok(())
+    }
+
+    /// Append the chunk found at `offset`; chunks come in order
+    pub fn stage(&mut self, offset: usize, data: &[u8]) -> Result<(), FwError> {
+        let Step::Receiving { image, size } = &mut self.step else {
+            return Err(FwError::Busy);
+        };
+        if offset != image.len() || image.len() + data.len() > *size {
+            return Err(FwError::InvalidArgument);
+        }
+        image.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Drop a staged or verified image
+    pub fn abort(&mut self) -> Result<(), FwError> {
+        match self.step {
+            Step::Activated { .. } => Err(FwError::Busy),
+            _ => {
+                self.step = Step::Idle;
+                Ok(())
+            }
+        }
+    }
+
+    /// Check the complete image: signature, target device and size
+    pub fn verify(&mut self, device: &mut dyn FirmwareDevice) -> Result<ImageHeader, FwError> {
+        let image = match &mut self.step {
+            Step::Receiving { image, size } if image.len() == *size => core::mem::take(image),
+            Step::Receiving { .. } => return Err(FwError::InvalidArgument),
+            _ => return Err(FwError::NotFound),
+        };
+        // A failed check drops the image: it has to be sent again anyway
+        self.step = Step::Idle;
+        if !self.locked || self.keys.is_empty() {
+            return Err(FwError::Rejected);
+        }
+
+        let (header, payload) = image::verify(&image, &self.keys)?;
+        let (vendor_id, device_id) = device.identity();
+        if !header.matches(vendor_id, device_id) {
+            return Err(FwError::WrongDevice);
+        }
+        if payload.len() > device.firmware_info()?.max_size as usize {
+            return Err(FwError::TooLarge);
+        }
+        self.step = Step::Verified { image };
+        Ok(header)
+    }
+
+    /// Write the verified image to the staging slot and run it; it has to
+    /// be confirmed within `confirm_ns`. Returns the slot now running.
+    pub fn activate(&mut self, device: &mut dyn FirmwareDevice, now: u64, confirm_ns: u64) -> Result<u8, FwError> {
+        let Step::Verified { image, .. } = &self.step else {
+            return Err(FwError::NotFound);
+        };
+        let info = device.firmware_info()?;
+        let payload = &image[HEADER_SIZE..image.len() - TRAILER_SIZE];
+        device.write_slot(info.staging_slot, payload)?;
+
+        if let Err(error) = device.activate_slot(info.staging_slot) {
+            self.step = Step::Idle;
+            if info.can_rollback {
+                device.restore_slot(info.active_slot)?;
+            }
+            return Err(error);
+        }
+        self.step = Step::Activated {
+            previous_slot: info.active_slot,
+            can_rollback: info.can_rollback,
+            deadline: now.saturating_add(confirm_ns),
+        };
+        Ok(info.staging_slot)
+    }
+
+    /// Keep the activated image
+    pub fn confirm(&mut self) -> Result<(), FwError> {
+        match self.step {
+            Step::Activated { .. } => {
+                self.step = Step::Idle;
+                Ok(())
+            }
+            _ => Err(FwError::NotFound),
+        }
+    }
+
+    /// Go back to the image that ran before the activation
+    pub fn rollback(&mut self, device: &mut dyn FirmwareDevice) -> Result<(), FwError> {
+        let Step::Activated { previous_slot, can_rollback, .. } = self.step else {
+            return Err(FwError::NotFound);
+        };
+        if !can_rollback {
+            return Err(FwError::NoRollback);
+        }
+        self.step = Step::Idle;
+        device.restore_slot(previous_slot)
+    }
+
+    /// Roll back an activation left unconfirmed past its deadline; returns
+    /// the outcome when it did
+    pub fn tick(&mut self, device: &mut dyn FirmwareDevice, now: u64) -> Option<Result<(), FwError>> {
+        match self.step {
+            Step::Activated { can_rollback: true, deadline, .. } if now >= deadline => Some(self.rollback(device)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::image::tests::{header, SECRET};
+    use crate::image::ANY_DEVICE;
+    use crate::FirmwareInfo;
+    use alloc::vec;
+    use orion_crypto::ed25519;
+
+    /// Two-slot device that refuses to run images starting with 0xFF
+    pub struct FakeDevice {
+        pub slots: [Vec<u8>; 2],
+        pub active: u8,
+    }
+
+    impl FakeDevice {
+        pub fn new() -> Self {
+            Self { slots: [b"old".to_vec(), Vec::new()], active: 1 }
+        }
+    }
+
+    impl FirmwareDevice for FakeDevice {
+        fn identity(&self) -> (u16, u16) {
+            (0x144d, 0xa808)
+        }
+
+        fn firmware_info(&mut self) -> Result<FirmwareInfo, FwError> {
+            Ok(FirmwareInfo {
+                version: [0; 16],
+                active_slot: self.active,
+                staging_slot: 3 - self.active,
+                can_rollback: true,
+                max_size: 1024,
+            })
+        }
+
+        fn write_slot(&mut self, slot: u8, payload: &[u8]) -> Result<(), FwError> {
+            self.slots[slot as usize - 1] = payload.to_vec();
+            Ok(())
+        }
+
+        fn activate_slot(&mut self, slot: u8) -> Result<(), FwError> {
+            if self.slots[slot as usize - 1].first() == Some(&0xFF) {
+                return Err(FwError::Device);
+            }
+            self.active = slot;
+            Ok(())
+        }
+
+        fn read_image(&mut self, offset: u32, length: u32) -> Result<Vec<u8>, FwError> {
+            let image = &self.slots[self.active as usize - 1];
+            let start = offset as usize;
+            if start > image.len() {
+                return Err(FwError::InvalidArgument);
+            }
+            Ok(image[start..image.len().min(start + length as usize)].to_vec())
+        }
+    }
+
+    /// I/O server answering with the given anchors
+    struct Anchors(Vec<[u8; 32]>);
+
+    impl Transport for Anchors {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            assert_eq!(request, IO_OP_TRUST_ANCHORS.to_le_bytes());
+            let mut reply = STATUS_OK.to_le_bytes().to_vec();
+            reply.extend_from_slice(&(self.0.len() as u32).to_le_bytes());
+            self.0.iter().for_each(|key| reply.extend_from_slice(key));
+            Some(reply)
+        }
+    }
+
+    pub fn updater() -> FirmwareUpdater {
+        let mut updater = FirmwareUpdater::new();
+        let mut io_server = Anchors(vec![[1; 32], ed25519::public_key(&SECRET)]);
+        assert_eq!(updater.load_trust_anchors(&mut io_server), Ok(2));
+        updater
+    }
+
+    fn send(updater: &mut FirmwareUpdater, image: &[u8]) {
+        updater.begin(image.len(), 1024).unwrap();
+        for (index, chunk) in image.chunks(100).enumerate() {
+            updater.stage(index * 100, chunk).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_update_confirm_and_deadline_rollback() {
+        let mut device = FakeDevice::new();
+        let mut updater = updater();
+        assert_eq!(updater.enroll_key(&[1; 32]), Err(FwError::Locked));
+
+        let image = image::build(&header(0x144d, ANY_DEVICE), &[0x42; 300], &SECRET);
+        send(&mut updater, &image);
+        // Nothing is written before verification
+        assert_eq!(updater.activate(&mut device, 0, 1000), Err(FwError::NotFound));
+        updater.verify(&mut device).unwrap();
+        assert_eq!(updater.activate(&mut device, 0, 1000), Ok(2));
+        assert_eq!(device.active, 2);
+        assert_eq!(updater.tick(&mut device, 999), None);
+        updater.confirm().unwrap();
+        assert_eq!(updater.state(), UpdateState::Idle);
+
+        // An update left unconfirmed goes back to the previous slot
+        send(&mut updater, &image);
+        updater.verify(&mut device).unwrap();
+        assert_eq!(updater.activate(&mut device, 0, 1000), Ok(1));
+        assert_eq!(updater.tick(&mut device, 1000), Some(Ok(())));
+        assert_eq!(device.active, 2);
+        assert_eq!(updater.rollback(&mut device), Err(FwError::NotFound));
+    }
+
+    #[test]
+    fn test_bad_images_never_reach_the_device() {
+        let mut device = FakeDevice::new();
+        let mut updater = updater();
+
+        let other = image::build(&header(0x8086, 0x10d3), &[0x42; 300], &SECRET);
+        send(&mut updater, &other);
+        assert_eq!(updater.verify(&mut device), Err(FwError::WrongDevice));
+
+        let unsigned = image::build(&header(0x144d, ANY_DEVICE), &[0x42; 300], &[3; 32]);
+        send(&mut updater, &unsigned);
+        assert_eq!(updater.verify(&mut device), Err(FwError::Rejected));
+        assert_eq!(updater.begin(unsigned.len() + 1024, 1024), Err(FwError::TooLarge));
+        assert_eq!(updater.stage(0, &[0]), Err(FwError::Busy));
+        assert_eq!(device.slots[1], vec![]);
+
+        // An image that fails to start is replaced by the previous one
+        let broken = image::build(&header(0x144d, 0xa808), &[0xFF; 16], &SECRET);
+        send(&mut updater, &broken);
+        updater.verify(&mut device).unwrap();
+        assert_eq!(updater.activate(&mut device, 0, 1000), Err(FwError::Device));
+        assert_eq!((device.active, updater.state()), (1, UpdateState::Idle));
+    }
+}
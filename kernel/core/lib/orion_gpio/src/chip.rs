@@ -0,0 +1,128 @@
+/*
+ * Orion Operating System - GPIO Chips
+ *
+ * What a GPIO controller driver implements: reading and driving its
+ * lines, and, when the controller can interrupt, choosing which line
+ * changes raise an interrupt and telling which lines have one pending.
+ * Lines are numbered from 0 within the chip and hold physical levels;
+ * active-low lines are the business of GpioLines above.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+/// Most lines on one chip, so that pending interrupts fit a u32
+pub const GPIO_CHIP_MAX_LINES: u32 = 32;
+
+/// What makes a line interrupt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    None = 0,
+    Rising = 1,
+    Falling = 2,
+    Both = 3,
+    High = 4,
+    Low = 5,
+}
+
+impl Trigger {
+    pub fn from_u32(value: u32) -> Option<Trigger> {
+        match value {
+            0 => Some(Trigger::None),
+            1 => Some(Trigger::Rising),
+            2 => Some(Trigger::Falling),
+            3 => Some(Trigger::Both),
+            4 => Some(Trigger::High),
+            5 => Some(Trigger::Low),
+            _ => None,
+        }
+    }
+
+    pub fn is_level(self) -> bool {
+        matches!(self, Trigger::High | Trigger::Low)
+    }
+
+    /// The same trigger on the physical line of an active-low one
+    pub fn inverted(self) -> Trigger {
+        match self {
+            Trigger::Rising => Trigger::Falling,
+            Trigger::Falling => Trigger::Rising,
+            Trigger::High => Trigger::Low,
+            Trigger::Low => Trigger::High,
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioError {
+    /// No such line on the chip
+    InvalidLine,
+    /// The line is requested by someone else
+    Busy,
+    /// The line is not requested by the caller
+    NotOwner,
+    /// An output was read as an input, or the reverse
+    WrongDirection,
+    /// The chip cannot do it, e.g. interrupt on that trigger
+    NotSupported,
+    InvalidArgument,
+    IoError,
+}
+
+impl GpioError {
+    /// Reply status, a negative errno
+    pub fn status(self) -> i32 {
+        match self {
+            GpioError::InvalidLine => -22,
+            GpioError::Busy => -16,
+            GpioError::NotOwner => -1,
+            GpioError::WrongDirection => -1,
+            GpioError::NotSupported => -95,
+            GpioError::InvalidArgument => -22,
+            GpioError::IoError => -5,
+        }
+    }
+}
+
+/// A GPIO controller
+pub trait GpioChip {
+    /// Name of the chip, e.g. "pl061"
+    fn label(&self) -> &str;
+
+    /// Number of lines, at most GPIO_CHIP_MAX_LINES
+    fn lines(&self) -> u32;
+
+    fn direction_input(&mut self, line: u32) -> Result<(), GpioError>;
+
+    /// Make `line` an output driving `value`
+    fn direction_output(&mut self, line: u32, value: bool) -> Result<(), GpioError>;
+
+    /// Level of the line, whatever its direction
+    fn get(&mut self, line: u32) -> Result<bool, GpioError>;
+
+    fn set(&mut self, line: u32, value: bool) -> Result<(), GpioError>;
+
+    /// Interrupt on `trigger`, or stop interrupting with Trigger::None.
+    /// Chips without an interrupt only accept the latter.
+    fn set_trigger(&mut self, line: u32, trigger: Trigger) -> Result<(), GpioError> {
+        let _ = line;
+        match trigger {
+            Trigger::None => Ok(()),
+            _ => Err(GpioError::NotSupported),
+        }
+    }
+
+    /// Lines with an interrupt pending, one bit each
+    fn pending(&mut self) -> u32 {
+        0
+    }
+
+    /// Clear the pending interrupts of the lines in `mask`
+    fn ack(&mut self, mask: u32) {
+        let _ = mask;
+    }
+}
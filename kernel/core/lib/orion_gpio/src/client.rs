@@ -0,0 +1,181 @@
+/*
+ * Orion Operating System - GPIO Client
+ *
+ * Client side of the GPIO control requests, used by drivers wired to
+ * GPIO lines (resets, enables, interrupt lines of I2C devices) and by
+ * tools. The transport is a trait so callers can use the controller
+ * driver's IPC channel and tests call straight into GpioController.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::chip::Trigger;
+use crate::control::{
+    put_string, read_string, read_u32, read_u64, GPIO_CTRL_EVENTS, GPIO_CTRL_GET, GPIO_CTRL_INFO, GPIO_CTRL_RELEASE,
+    GPIO_CTRL_REQUEST, GPIO_CTRL_SET, STATUS_EIO, STATUS_OK,
+};
+use crate::lines::{LineConfig, LineEvent, LineInfo};
+
+/// Carries a request to a controller driver and returns its reply,
+/// None when the driver did not answer
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// A chip as INFO describes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChipInfo {
+    pub label: String,
+    pub lines: Vec<LineInfo>,
+}
+
+pub struct GpioClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> GpioClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.transport.call(request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn line_request(opcode: u32, line: u32, arguments: &[u32]) -> Vec<u8> {
+        let mut request = Vec::with_capacity(8 + arguments.len() * 4);
+        for field in [opcode, line].iter().chain(arguments) {
+            request.extend_from_slice(&field.to_le_bytes());
+        }
+        request
+    }
+
+    pub fn info(&mut self) -> Result<ChipInfo, i32> {
+        let payload = self.call(&GPIO_CTRL_INFO.to_le_bytes())?;
+        let (label, mut offset) = read_string(&payload, 0).ok_or(STATUS_EIO)?;
+        let count = read_u32(&payload, offset).ok_or(STATUS_EIO)?;
+        offset += 4;
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            let flags = read_u32(&payload, offset).ok_or(STATUS_EIO)?;
+            let trigger = read_u32(&payload, offset + 4).and_then(Trigger::from_u32).ok_or(STATUS_EIO)?;
+            let (consumer, next) = read_string(&payload, offset + 8).ok_or(STATUS_EIO)?;
+            lines.push(LineInfo { consumer, flags, trigger });
+            offset = next;
+        }
+        Ok(ChipInfo { label, lines })
+    }
+
+    pub fn request(&mut self, line: u32, consumer: &str, config: LineConfig) -> Result<(), i32> {
+        let mut request = Self::line_request(GPIO_CTRL_REQUEST, line, &[config.flags(), config.trigger as u32]);
+        put_string(&mut request, consumer);
+        self.call(&request).map(drop)
+    }
+
+    pub fn release(&mut self, line: u32) -> Result<(), i32> {
+        self.call(&Self::line_request(GPIO_CTRL_RELEASE, line, &[])).map(drop)
+    }
+
+    pub fn get(&mut self, line: u32) -> Result<bool, i32> {
+        let payload = self.call(&Self::line_request(GPIO_CTRL_GET, line, &[]))?;
+        Ok(read_u32(&payload, 0).ok_or(STATUS_EIO)? != 0)
+    }
+
+    pub fn set(&mut self, line: u32, active: bool) -> Result<(), i32> {
+        self.call(&Self::line_request(GPIO_CTRL_SET, line, &[active as u32])).map(drop)
+    }
+
+    /// The events queued on `line`; with `wait`, blocks until there is one
+    pub fn events(&mut self, line: u32, wait: bool) -> Result<Vec<LineEvent>, i32> {
+        let payload = self.call(&Self::line_request(GPIO_CTRL_EVENTS, line, &[wait as u32]))?;
+        let count = read_u32(&payload, 0).ok_or(STATUS_EIO)? as usize;
+        (0..count)
+            .map(|index| {
+                let offset = 4 + index * 12;
+                let active = read_u32(&payload, offset).ok_or(STATUS_EIO)? != 0;
+                let timestamp = read_u64(&payload, offset + 4).ok_or(STATUS_EIO)?;
+                Ok(LineEvent { line, active, timestamp })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{GpioController, Replies, STATUS_EINTR, STATUS_EPERM};
+    use crate::lines::tests::FakeChip;
+    use crate::lines::{LINE_OUTPUT, LINE_USED};
+    use alloc::vec;
+
+    struct Direct<'a> {
+        controller: &'a mut GpioController<FakeChip>,
+        sender: u64,
+        admin: bool,
+    }
+
+    impl Transport for Direct<'_> {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            let mut replies = self.controller.handle(self.sender, request, self.admin);
+            replies.pop().map(|(_, response)| response)
+        }
+    }
+
+    fn client(controller: &mut GpioController<FakeChip>, sender: u64, admin: bool) -> GpioClient<Direct<'_>> {
+        GpioClient::new(Direct { controller, sender, admin })
+    }
+
+    fn statuses(replies: &Replies) -> Vec<(u64, i32)> {
+        replies.iter().map(|(to, response)| (*to, read_u32(response, 0).unwrap() as i32)).collect()
+    }
+
+    #[test]
+    fn test_drives_lines_through_control_requests() {
+        let mut controller = GpioController::new(FakeChip::new());
+        assert_eq!(client(&mut controller, 1, false).request(0, "led", LineConfig::output(false)), Err(STATUS_EPERM));
+        let mut admin = client(&mut controller, 1, true);
+        admin.request(0, "led", LineConfig::output(false)).unwrap();
+        admin.set(0, true).unwrap();
+        assert_eq!(admin.get(0), Ok(true));
+        admin.request(5, "irq", LineConfig::input(Trigger::Both)).unwrap();
+
+        let info = client(&mut controller, 2, false).info().unwrap();
+        assert_eq!((info.label.as_str(), info.lines.len()), ("fake", 8));
+        assert_eq!((info.lines[0].flags, info.lines[0].consumer.as_str()), (LINE_USED | LINE_OUTPUT, "led"));
+        assert_eq!(info.lines[5].trigger, Trigger::Both);
+        assert_eq!(client(&mut controller, 2, false).set(0, false), Err(crate::chip::GpioError::NotOwner.status()));
+
+        // A waiting EVENTS is answered by the interrupt
+        let events = GpioClient::<Direct>::line_request(GPIO_CTRL_EVENTS, 5, &[1]);
+        assert!(controller.handle(1, &events, false).is_empty());
+        controller.lines_mut().chip_mut().drive(5, true);
+        let replies = controller.interrupt(42);
+        assert_eq!(statuses(&replies), [(1, STATUS_OK)]);
+        assert_eq!(&replies[0].1[4..8], &1u32.to_le_bytes());
+
+        controller.lines_mut().chip_mut().drive(5, false);
+        controller.interrupt(43);
+        let queued = client(&mut controller, 1, false).events(5, true).unwrap();
+        assert_eq!(queued, vec![LineEvent { line: 5, active: false, timestamp: 43 }]);
+
+        // Releasing the line ends the wait on it
+        assert!(controller.handle(1, &events, false).is_empty());
+        let release = GpioClient::<Direct>::line_request(GPIO_CTRL_RELEASE, 5, &[]);
+        assert_eq!(statuses(&controller.handle(1, &release, false)), [(1, STATUS_EINTR), (1, STATUS_OK)]);
+
+        controller.disconnect(1);
+        assert_eq!(controller.lines().owner(0), None);
+    }
+}
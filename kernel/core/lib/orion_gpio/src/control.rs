@@ -0,0 +1,224 @@
+/*
+ * Orion Operating System - GPIO Control
+ *
+ * Requests a GPIO controller driver accepts on its endpoint, from other
+ * drivers and from tools. All fields are little-endian; every request
+ * starts with a 32-bit opcode and every reply with a 32-bit signed status
+ * (0 or a negative errno). Strings are a `len: u32` followed by UTF-8.
+ *
+ *   INFO                                  -> label lines:u32
+ *                                            {flags:u32 trigger:u32 consumer}*
+ *   REQUEST line:u32 flags:u32 trigger:u32 consumer
+ *   RELEASE line:u32
+ *   GET     line:u32                      -> active:u32
+ *   SET     line:u32 active:u32
+ *   EVENTS  line:u32 wait:u32             -> count:u32 {active:u32 timestamp:u64}*
+ *
+ * REQUEST takes a line (LINE_* flags, a Trigger for inputs) and needs
+ * CAP_ADMIN; the other requests but INFO are for the line's owner. EVENTS
+ * returns the events queued on the line; with `wait` set and none queued
+ * the reply comes when the line next fires, or with EINTR when the line
+ * is released meanwhile.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_proto::reply;
+
+use crate::chip::{GpioChip, GpioError, Trigger};
+use crate::lines::{GpioLines, LineConfig, LineEvent};
+
+// Opcodes
+pub const GPIO_CTRL_INFO: u32 = 0x7101;
+pub const GPIO_CTRL_REQUEST: u32 = 0x7102;
+pub const GPIO_CTRL_RELEASE: u32 = 0x7103;
+pub const GPIO_CTRL_GET: u32 = 0x7104;
+pub const GPIO_CTRL_SET: u32 = 0x7105;
+pub const GPIO_CTRL_EVENTS: u32 = 0x7106;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_EINTR: i32 = -4;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+
+/// Replies to send, and to whom
+pub type Replies = Vec<(u64, Vec<u8>)>;
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+pub(crate) fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + len))
+}
+
+pub(crate) fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn encode_events(events: &[LineEvent]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(4 + events.len() * 12);
+    payload.extend_from_slice(&(events.len() as u32).to_le_bytes());
+    for event in events {
+        payload.extend_from_slice(&(event.active as u32).to_le_bytes());
+        payload.extend_from_slice(&event.timestamp.to_le_bytes());
+    }
+    payload
+}
+
+/// A chip's lines served on the driver's endpoint
+pub struct GpioController<C: GpioChip> {
+    lines: GpioLines<C>,
+    /// Owners waiting on EVENTS, and the line
+    waiters: Vec<(u64, u32)>,
+}
+
+impl<C: GpioChip> GpioController<C> {
+    pub fn new(chip: C) -> Self {
+        Self { lines: GpioLines::new(chip), waiters: Vec::new() }
+    }
+
+    pub fn lines(&self) -> &GpioLines<C> {
+        &self.lines
+    }
+
+    pub fn lines_mut(&mut self) -> &mut GpioLines<C> {
+        &mut self.lines
+    }
+
+    /// Serve a request from `sender`. `admin` is whether the sender
+    /// holds CAP_ADMIN, which the driver checks
+    pub fn handle(&mut self, sender: u64, request: &[u8], admin: bool) -> Replies {
+        let mut replies = Vec::new();
+        let line = read_u32(request, 4);
+        let response = match (read_u32(request, 0), line) {
+            (Some(GPIO_CTRL_INFO), _) => self.info(),
+            (Some(GPIO_CTRL_REQUEST), Some(_)) if !admin => reply(STATUS_EPERM, &[]),
+            (Some(GPIO_CTRL_REQUEST), Some(line)) => {
+                let parsed = (|| {
+                    let flags = read_u32(request, 8)?;
+                    let trigger = Trigger::from_u32(read_u32(request, 12)?)?;
+                    let (consumer, _) = read_string(request, 16)?;
+                    Some((LineConfig::from_flags(flags, trigger), consumer))
+                })();
+                match parsed {
+                    Some((config, consumer)) => status(self.lines.request(sender, line, &consumer, config)),
+                    None => reply(STATUS_EINVAL, &[]),
+                }
+            }
+            (Some(GPIO_CTRL_RELEASE), Some(line)) => {
+                let result = self.lines.release(sender, line);
+                if result.is_ok() {
+                    replies.extend(self.cancel(line));
+                }
+                status(result)
+            }
+            (Some(GPIO_CTRL_GET), Some(line)) => match self.lines.get(sender, line) {
+                Ok(active) => reply(STATUS_OK, &(active as u32).to_le_bytes()),
+                Err(error) => reply(error.status(), &[]),
+            },
+            (Some(GPIO_CTRL_SET), Some(line)) => match read_u32(request, 8) {
+                Some(active) => status(self.lines.set(sender, line, active != 0)),
+                None => reply(STATUS_EINVAL, &[]),
+            },
+            (Some(GPIO_CTRL_EVENTS), Some(line)) => {
+                let wait = read_u32(request, 8).unwrap_or(0) != 0;
+                if wait && self.lines.owner(line) == Some(sender) && !self.lines.has_events(line) {
+                    if self.waiters.iter().any(|&(_, waiting)| waiting == line) {
+                        reply(STATUS_EBUSY, &[])
+                    } else {
+                        self.waiters.push((sender, line));
+                        return replies;
+                    }
+                } else {
+                    match self.lines.events(sender, line) {
+                        Ok(events) => reply(STATUS_OK, &encode_events(&events)),
+                        Err(error) => reply(error.status(), &[]),
+                    }
+                }
+            }
+            _ => reply(STATUS_EINVAL, &[]),
+        };
+        replies.push((sender, response));
+        replies
+    }
+
+    /// Handle an interrupt of the chip at `now`; the replies of the
+    /// EVENTS waiting on the lines that fired
+    pub fn interrupt(&mut self, now: u64) -> Replies {
+        let fired = self.lines.interrupt(now);
+        let mut replies = Vec::new();
+        let mut index = 0;
+        while index < self.waiters.len() {
+            let (owner, line) = self.waiters[index];
+            if !fired.contains(&line) {
+                index += 1;
+                continue;
+            }
+            self.waiters.remove(index);
+            let response = match self.lines.events(owner, line) {
+                Ok(events) => reply(STATUS_OK, &encode_events(&events)),
+                Err(error) => reply(error.status(), &[]),
+            };
+            replies.push((owner, response));
+        }
+        replies
+    }
+
+    /// Release the lines of a client that went away
+    pub fn disconnect(&mut self, owner: u64) {
+        self.waiters.retain(|&(waiting, _)| waiting != owner);
+        self.lines.release_all(owner);
+    }
+
+    /// Answer the wait on a released line
+    fn cancel(&mut self, line: u32) -> Replies {
+        let mut replies = Vec::new();
+        self.waiters.retain(|&(owner, waiting)| {
+            let keep = waiting != line;
+            if !keep {
+                replies.push((owner, reply(STATUS_EINTR, &[])));
+            }
+            keep
+        });
+        replies
+    }
+
+    fn info(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        put_string(&mut payload, self.lines.chip().label());
+        payload.extend_from_slice(&self.lines.count().to_le_bytes());
+        for info in (0..self.lines.count()).filter_map(|line| self.lines.info(line)) {
+            payload.extend_from_slice(&info.flags.to_le_bytes());
+            payload.extend_from_slice(&(info.trigger as u32).to_le_bytes());
+            put_string(&mut payload, &info.consumer);
+        }
+        reply(STATUS_OK, &payload)
+    }
+}
+
+fn status(result: Result<(), GpioError>) -> Vec<u8> {
+    match result {
+        Ok(()) => reply(STATUS_OK, &[]),
+        Err(error) => reply(error.status(), &[]),
+    }
+}
@@ -0,0 +1,33 @@
+/*
+ * Orion Operating System - GPIO
+ *
+ * Framework of the GPIO controller drivers. A driver implements GpioChip
+ * for its controller (see chip.rs) and serves a GpioController on its
+ * endpoint (see control.rs), calling interrupt when the controller
+ * interrupts. Lines are requested by one owner at a time, inputs with an
+ * edge or level trigger whose events the owner fetches or waits for
+ * (see lines.rs). Drivers and tools use GpioClient.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod chip;
+pub mod client;
+pub mod control;
+pub mod lines;
+
+pub use chip::{GpioChip, GpioError, Trigger, GPIO_CHIP_MAX_LINES};
+pub use client::{ChipInfo, GpioClient, Transport};
+pub use control::{GpioController, Replies};
+pub use lines::{
+    GpioLines, LineConfig, LineEvent, LineInfo, EVENT_QUEUE_MAX, LINE_ACTIVE_LOW, LINE_OUTPUT, LINE_OUTPUT_ACTIVE,
+    LINE_USED,
+};
@@ -0,0 +1,403 @@
+/*
+ * Orion Operating System - GPIO Lines
+ *
+ * The lines of a chip and who uses them. A line is requested by one
+ * client at a time, a driver or a tool, as an input or an output, maybe
+ * active-low; values read and written through here are logical, true
+ * meaning active. An input can be requested with a trigger: when the
+ * chip interrupts, the driver loop calls interrupt and an event is
+ * queued on each line that fired, for its owner to fetch. A level
+ * trigger fires once and is off until the owner fetches the events, or
+ * the interrupt would keep coming for as long as the level holds.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::chip::{GpioChip, GpioError, Trigger};
+
+// Request flags
+pub const LINE_OUTPUT: u32 = 1 << 0;
+pub const LINE_ACTIVE_LOW: u32 = 1 << 1;
+/// An output starts active
+pub const LINE_OUTPUT_ACTIVE: u32 = 1 << 2;
+/// Reported by INFO for a requested line
+pub const LINE_USED: u32 = 1 << 3;
+
+/// Events kept per line; past that the oldest are dropped
+pub const EVENT_QUEUE_MAX: usize = 16;
+
+/// Longest consumer name
+pub const CONSUMER_MAX: usize = 32;
+
+/// How a line is requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    pub output: bool,
+    pub active_low: bool,
+    /// Initial value of an output
+    pub active: bool,
+    /// For inputs only
+    pub trigger: Trigger,
+}
+
+impl LineConfig {
+    pub fn input(trigger: Trigger) -> Self {
+        Self { output: false, active_low: false, active: false, trigger }
+    }
+
+    pub fn output(active: bool) -> Self {
+        Self { output: true, active_low: false, active, trigger: Trigger::None }
+    }
+
+    pub fn active_low(self) -> Self {
+        Self { active_low: true, ..self }
+    }
+
+    pub fn flags(&self) -> u32 {
+        let mut flags = 0;
+        if self.output {
+            flags |= LINE_OUTPUT;
+        }
+        if self.active_low {
+            flags |= LINE_ACTIVE_LOW;
+        }
+        if self.active {
+            flags |= LINE_OUTPUT_ACTIVE;
+        }
+        flags
+    }
+
+    pub fn from_flags(flags: u32, trigger: Trigger) -> Self {
+        Self {
+            output: flags & LINE_OUTPUT != 0,
+            active_low: flags & LINE_ACTIVE_LOW != 0,
+            active: flags & LINE_OUTPUT_ACTIVE != 0,
+            trigger,
+        }
+    }
+}
+
+/// A trigger firing on a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEvent {
+    pub line: u32,
+    /// Logical value the line went to, or holds for a level trigger
+    pub active: bool,
+    pub timestamp: u64,
+}
+
+/// What INFO reports about a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineInfo {
+    pub consumer: String,
+    /// LINE_* flags
+    pub flags: u32,
+    pub trigger: Trigger,
+}
+
+#[derive(Default)]
+struct Line {
+    owner: Option<u64>,
+    consumer: String,
+    config: Option<LineConfig>,
+    /// Whether a level trigger may fire again
+    armed: bool,
+    events: VecDeque<LineEvent>,
+    dropped: u64,
+}
+
+pub struct GpioLines<C: GpioChip> {
+    chip: C,
+    lines: Vec<Line>,
+}
+
+impl<C: GpioChip> GpioLines<C> {
+    pub fn new(chip: C) -> Self {
+        let count = chip.lines().min(crate::chip::GPIO_CHIP_MAX_LINES);
+        Self { chip, lines: (0..count).map(|_| Line::default()).collect() }
+    }
+
+    pub fn chip(&self) -> &C {
+        &self.chip
+    }
+
+    pub fn chip_mut(&mut self) -> &mut C {
+        &mut self.chip
+    }
+
+    pub fn count(&self) -> u32 {
+        self.lines.len() as u32
+    }
+
+    pub fn owner(&self, line: u32) -> Option<u64> {
+        self.lines.get(line as usize)?.owner
+    }
+
+    pub fn info(&self, line: u32) -> Option<LineInfo> {
+        let state = self.lines.get(line as usize)?;
+        Some(match state.config {
+            Some(config) => LineInfo {
+                consumer: state.consumer.clone(),
+                flags: config.flags() | LINE_USED,
+                trigger: config.trigger,
+            },
+            None => LineInfo { consumer: String::new(), flags: 0, trigger: Trigger::None },
+        })
+    }
+
+    /// Events dropped on `line` because its owner did not fetch them
+    pub fn dropped(&self, line: u32) -> u64 {
+        self.lines.get(line as usize).map_or(0, |state| state.dropped)
+    }
+
+    pub fn has_events(&self, line: u32) -> bool {
+        self.lines.get(line as usize).is_some_and(|state| !state.events.is_empty())
+    }
+
+    /// The line if `owner` holds it
+    fn owned(&mut self, owner: u64, line: u32) -> Result<&mut Line, GpioError> {
+        let state = self.lines.get_mut(line as usize).ok_or(GpioError::InvalidLine)?;
+        match state.owner {
+            Some(holder) if holder == owner => Ok(state),
+            _ => Err(GpioError::NotOwner),
+        }
+    }
+
+    /// Take `line` for `owner`, or change how its owner uses it
+    pub fn request(&mut self, owner: u64, line: u32, consumer: &str, config: LineConfig) -> Result<(), GpioError> {
+        let state = self.lines.get(line as usize).ok_or(GpioError::InvalidLine)?;
+        if state.owner.is_some_and(|holder| holder != owner) {
+            return Err(GpioError::Busy);
+        }
+        if consumer.len() > CONSUMER_MAX || (config.output && config.trigger != Trigger::None) {
+            return Err(GpioError::InvalidArgument);
+        }
+        let physical = if config.active_low { config.trigger.inverted() } else { config.trigger };
+        self.chip.set_trigger(line, Trigger::None)?;
+        if config.output {
+            self.chip.direction_output(line, config.active != config.active_low)?;
+        } else {
+            self.chip.direction_input(line)?;
+            self.chip.set_trigger(line, physical)?;
+        }
+        let state = &mut self.lines[line as usize];
+        state.owner = Some(owner);
+        state.consumer = String::from(consumer);
+        state.config = Some(config);
+        state.armed = true;
+        state.events.clear();
+        Ok(())
+    }
+
+    /// Give `line` back, leaving it an input that does not interrupt
+    pub fn release(&mut self, owner: u64, line: u32) -> Result<(), GpioError> {
+        self.owned(owner, line)?;
+        self.lines[line as usize] = Line::default();
+        self.chip.set_trigger(line, Trigger::None)?;
+        self.chip.direction_input(line)
+    }
+
+    /// Release every line of `owner`, when it goes away; the lines
+    /// released
+    pub fn release_all(&mut self, owner: u64) -> Vec<u32> {
+        let held: Vec<u32> = (0..self.count()).filter(|&line| self.owner(line) == Some(owner)).collect();
+        for &line in &held {
+            let _ = self.release(owner, line);
+        }
+        held
+    }
+
+    pub fn get(&mut self, owner: u64, line: u32) -> Result<bool, GpioError> {
+        let active_low = self.owned(owner, line)?.config.is_some_and(|config| config.active_low);
+        Ok(self.chip.get(line)? != active_low)
+    }
+
+    pub fn set(&mut self, owner: u64, line: u32, active: bool) -> Result<(), GpioError> {
+        let config = self.owned(owner, line)?.config.ok_or(GpioError::NotOwner)?;
+        if !config.output {
+            return Err(GpioError::WrongDirection);
+        }
+        self.chip.set(line, active != config.active_low)
+    }
+
+    /// Fetch the events of `line`, oldest first, and arm its level
+    /// trigger again
+    pub fn events(&mut self, owner: u64, line: u32) -> Result<Vec<LineEvent>, GpioError> {
+        let state = self.owned(owner, line)?;
+        let events = state.events.drain(..).collect();
+        let config = state.config.ok_or(GpioError::NotOwner)?;
+        if !state.armed {
+            state.armed = true;
+            let physical = if config.active_low { config.trigger.inverted() } else { config.trigger };
+            self.chip.set_trigger(line, physical)?;
+        }
+        Ok(events)
+    }
+
+    /// Handle an interrupt of the chip at `now`; the lines that got an
+    /// event
+    pub fn interrupt(&mut self, now: u64) -> Vec<u32> {
+        let pending = self.chip.pending() & (u32::MAX >> (32 - self.count().max(1)));
+        let mut fired = Vec::new();
+        for line in (0..self.count()).filter(|line| pending & (1 << line) != 0) {
+            let state = &self.lines[line as usize];
+            let Some(config) = state.config.filter(|config| config.trigger != Trigger::None && state.armed) else {
+                continue;
+            };
+            let active = match config.trigger {
+                Trigger::Rising | Trigger::High => true,
+                Trigger::Falling | Trigger::Low => false,
+                // The edge is told by where the line went
+                _ => match self.chip.get(line) {
+                    Ok(level) => level != config.active_low,
+                    Err(_) => continue,
+                },
+            };
+            if config.trigger.is_level() {
+                let _ = self.chip.set_trigger(line, Trigger::None);
+            }
+            let state = &mut self.lines[line as usize];
+            state.armed = !config.trigger.is_level();
+            if state.events.len() == EVENT_QUEUE_MAX {
+                state.events.pop_front();
+                state.dropped += 1;
+            }
+            state.events.push_back(LineEvent { line, active, timestamp: now });
+            fired.push(line);
+        }
+        self.chip.ack(pending);
+        fired
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Eight lines whose levels the test drives, latching interrupts the
+    /// way a controller does
+    pub struct FakeChip {
+        pub levels: u32,
+        pub outputs: u32,
+        pub triggers: Vec<Trigger>,
+        pub latched: u32,
+    }
+
+    impl FakeChip {
+        pub fn new() -> Self {
+            Self { levels: 0, outputs: 0, triggers: vec![Trigger::None; 8], latched: 0 }
+        }
+
+        /// Drive input `line` to `level` from outside
+        pub fn drive(&mut self, line: u32, level: bool) {
+            let before = self.levels & (1 << line) != 0;
+            self.levels = (self.levels & !(1 << line)) | ((level as u32) << line);
+            let fires = match self.triggers[line as usize] {
+                Trigger::Rising => !before && level,
+                Trigger::Falling => before && !level,
+                Trigger::Both => before != level,
+                Trigger::High => level,
+                Trigger::Low => !level,
+                Trigger::None => false,
+            };
+            if fires {
+                self.latched |= 1 << line;
+            }
+        }
+    }
+
+    impl GpioChip for FakeChip {
+        fn label(&self) -> &str {
+            "fake"
+        }
+
+        fn lines(&self) -> u32 {
+            8
+        }
+
+        fn direction_input(&mut self, line: u32) -> Result<(), GpioError> {
+            self.outputs &= !(1 << line);
+            Ok(())
+        }
+
+        fn direction_output(&mut self, line: u32, value: bool) -> Result<(), GpioError> {
+            self.outputs |= 1 << line;
+            self.set(line, value)
+        }
+
+        fn get(&mut self, line: u32) -> Result<bool, GpioError> {
+            Ok(self.levels & (1 << line) != 0)
+        }
+
+        fn set(&mut self, line: u32, value: bool) -> Result<(), GpioError> {
+            self.levels = (self.levels & !(1 << line)) | ((value as u32) << line);
+            Ok(())
+        }
+
+        fn set_trigger(&mut self, line: u32, trigger: Trigger) -> Result<(), GpioError> {
+            self.triggers[line as usize] = trigger;
+            Ok(())
+        }
+
+        fn pending(&mut self) -> u32 {
+            self.latched
+        }
+
+        fn ack(&mut self, mask: u32) {
+            self.latched &= !mask;
+        }
+    }
+
+    #[test]
+    fn test_lines_are_owned_and_logical() {
+        let mut lines = GpioLines::new(FakeChip::new());
+        lines.request(1, 0, "reset", LineConfig::output(true).active_low()).unwrap();
+        assert_eq!(lines.chip().levels & 1, 0);
+        assert_eq!(lines.request(2, 0, "other", LineConfig::input(Trigger::None)), Err(GpioError::Busy));
+        assert_eq!(lines.set(2, 0, false), Err(GpioError::NotOwner));
+        lines.set(1, 0, false).unwrap();
+        assert_eq!((lines.chip().levels & 1, lines.get(1, 0)), (1, Ok(false)));
+        assert_eq!(lines.info(0).unwrap().flags, LINE_USED | LINE_OUTPUT | LINE_ACTIVE_LOW | LINE_OUTPUT_ACTIVE);
+        assert_eq!(lines.request(1, 8, "reset", LineConfig::output(true)), Err(GpioError::InvalidLine));
+
+        lines.request(1, 1, "button", LineConfig::input(Trigger::None)).unwrap();
+        assert_eq!(lines.set(1, 1, true), Err(GpioError::WrongDirection));
+        assert_eq!(lines.release_all(1), [0, 1]);
+        assert_eq!((lines.owner(0), lines.chip().outputs), (None, 0));
+    }
+
+    #[test]
+    fn test_interrupts_queue_events() {
+        let mut lines = GpioLines::new(FakeChip::new());
+        lines.request(1, 2, "button", LineConfig::input(Trigger::Falling).active_low()).unwrap();
+        lines.request(1, 3, "alert", LineConfig::input(Trigger::High)).unwrap();
+        assert_eq!(lines.chip().triggers[2], Trigger::Rising);
+
+        // Pressing the active-low button raises the line
+        lines.chip_mut().drive(2, true);
+        lines.chip_mut().drive(3, true);
+        assert_eq!(lines.interrupt(10), [2, 3]);
+        assert_eq!(lines.events(1, 2), Ok(vec![LineEvent { line: 2, active: false, timestamp: 10 }]));
+
+        // The level trigger stays off until its events are fetched
+        assert_eq!((lines.chip().triggers[3], lines.chip().latched), (Trigger::None, 0));
+        assert_eq!(lines.events(1, 3).unwrap().len(), 1);
+        assert_eq!(lines.chip().triggers[3], Trigger::High);
+
+        for now in 0..2 * EVENT_QUEUE_MAX as u64 + 4 {
+            lines.chip_mut().drive(2, now % 2 == 0);
+            lines.interrupt(now);
+        }
+        assert_eq!((lines.events(1, 2).unwrap().len(), lines.dropped(2)), (EVENT_QUEUE_MAX, 1));
+        assert_eq!(lines.events(2, 2), Err(GpioError::NotOwner));
+    }
+}
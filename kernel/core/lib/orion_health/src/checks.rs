@@ -0,0 +1,136 @@
+/*
+ * Orion Operating System - Health Checks
+ *
+ * The checks a server runs when the health server asks whether it is
+ * alive and ready. A server registers every check once, with the
+ * function running it against the server's own state, and hands the
+ * requests it receives to serve, which answers CHECK and leaves every
+ * other request to the server.
+ *
+ * Checks run on the server's message loop, between two requests: they
+ * look at the state the server keeps and must not block.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::protocol::{encode_checks, read_u32, CheckResult, Probe, HEALTH_OP_CHECK, STATUS_OK};
+
+/// A check on the state `S` of a server, Err with the reason it failed
+pub type CheckFn<S> = fn(&S) -> Result<(), String>;
+
+struct Check<S> {
+    probe: Probe,
+    name: &'static str,
+    run: CheckFn<S>,
+}
+
+/// The liveness and readiness checks of a server
+pub struct HealthChecks<S> {
+    checks: Vec<Check<S>>,
+}
+
+impl<S> Default for HealthChecks<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> HealthChecks<S> {
+    pub fn new() -> Self {
+        Self { checks: Vec::new() }
+    }
+
+    /// Add a check failing when the server has to be restarted
+    pub fn liveness(mut self, name: &'static str, run: CheckFn<S>) -> Self {
+        self.checks.push(Check { probe: Probe::Liveness, name, run });
+        self
+    }
+
+    /// Add a check failing while the server cannot serve requests
+    pub fn readiness(mut self, name: &'static str, run: CheckFn<S>) -> Self {
+        self.checks.push(Check { probe: Probe::Readiness, name, run });
+        self
+    }
+
+    /// Run every check, in the order they were added
+    pub fn run(&self, state: &S) -> Vec<CheckResult> {
+        self.checks
+            .iter()
+            .map(|check| {
+                let outcome = (check.run)(state);
+                CheckResult {
+                    probe: check.probe,
+                    name: String::from(check.name),
+                    passed: outcome.is_ok(),
+                    detail: outcome.err().unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Reply to `request` when it is a CHECK: i32 status followed by the
+    /// results of the checks. None for any other request
+    pub fn serve(&self, state: &S, request: &[u8]) -> Option<Vec<u8>> {
+        if !is_check(request) {
+            return None;
+        }
+        let mut reply = STATUS_OK.to_le_bytes().to_vec();
+        encode_checks(&self.run(state), &mut reply);
+        Some(reply)
+    }
+}
+
+/// Whether `request` is a CHECK
+pub fn is_check(request: &[u8]) -> bool {
+    read_u32(request, 0) == Some(HEALTH_OP_CHECK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{decode_checks, HealthState};
+
+    struct Server {
+        mounted: bool,
+    }
+
+    fn alive(_server: &Server) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn root_mounted(server: &Server) -> Result<(), String> {
+        if server.mounted {
+            Ok(())
+        } else {
+            Err(String::from("nothing mounted on /"))
+        }
+    }
+
+    #[test]
+    fn test_serves_check_requests_only() {
+        let checks = HealthChecks::new().liveness("loop", alive).readiness("root", root_mounted);
+        let server = Server { mounted: false };
+
+        assert_eq!(checks.serve(&server, &1u32.to_le_bytes()), None);
+        assert_eq!(checks.serve(&server, &[]), None);
+
+        let reply = checks.serve(&server, &HEALTH_OP_CHECK.to_le_bytes()).unwrap();
+        assert_eq!(read_u32(&reply, 0), Some(STATUS_OK as u32));
+        let (results, end) = decode_checks(&reply, 4).unwrap();
+        assert_eq!(end, reply.len());
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].probe, results[0].passed), (Probe::Liveness, true));
+        assert_eq!(results[1].detail, "nothing mounted on /");
+        assert_eq!(HealthState::of_checks(&results), HealthState::NotReady);
+
+        let results = checks.run(&Server { mounted: true });
+        assert_eq!(HealthState::of_checks(&results), HealthState::Healthy);
+    }
+}
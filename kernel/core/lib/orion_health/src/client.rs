@@ -0,0 +1,120 @@
+/*
+ * Orion Operating System - Health Client
+ *
+ * Client side of the health requests: the health server checks the
+ * servers through it, and the exporters, management tools and init ask
+ * the health server for its report. The transport is a trait so callers
+ * can use their IPC channel and tests a plain function.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::protocol::{
+    decode_checks, decode_report, put_string, read_u32, CheckResult, HealthReport, HEALTH_OP_CHECK, HEALTH_OP_REGISTER,
+    HEALTH_OP_STATUS, HEALTH_OP_UNREGISTER, STATUS_EIO, STATUS_OK,
+};
+
+/// Carries a request to a server and returns its reply, None when the
+/// server did not answer
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+pub struct HealthClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> HealthClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.transport.call(request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    /// Run the checks of the server at the other end
+    pub fn check(&mut self) -> Result<Vec<CheckResult>, i32> {
+        let payload = self.call(&HEALTH_OP_CHECK.to_le_bytes())?;
+        decode_checks(&payload, 0).map(|(checks, _)| checks).ok_or(STATUS_EIO)
+    }
+
+    /// Report of the health server
+    pub fn status(&mut self) -> Result<HealthReport, i32> {
+        let payload = self.call(&HEALTH_OP_STATUS.to_le_bytes())?;
+        decode_report(&payload).ok_or(STATUS_EIO)
+    }
+
+    /// Have the health server poll the server listening on channel `name`
+    pub fn register(&mut self, name: &str) -> Result<(), i32> {
+        let mut request = HEALTH_OP_REGISTER.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        self.call(&request).map(|_| ())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Result<(), i32> {
+        let mut request = HEALTH_OP_UNREGISTER.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        self.call(&request).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::HealthChecks;
+    use crate::protocol::{Probe, STATUS_EINVAL};
+    use alloc::string::String;
+
+    struct Pools {
+        offline: usize,
+    }
+
+    fn pools_online(pools: &Pools) -> Result<(), String> {
+        match pools.offline {
+            0 => Ok(()),
+            offline => Err(alloc::format!("{} pools offline", offline)),
+        }
+    }
+
+    /// A server answering CHECK and refusing everything else
+    struct Server(HealthChecks<Pools>, Pools);
+
+    impl Transport for Server {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            Some(self.0.serve(&self.1, request).unwrap_or_else(|| STATUS_EINVAL.to_le_bytes().to_vec()))
+        }
+    }
+
+    struct Silent;
+
+    impl Transport for Silent {
+        fn call(&mut self, _request: &[u8]) -> Option<Vec<u8>> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_checks_servers_through_the_transport() {
+        let checks = HealthChecks::new().readiness("pools", pools_online);
+        let mut client = HealthClient::new(Server(checks, Pools { offline: 2 }));
+        let results = client.check().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].probe, results[0].passed), (Probe::Readiness, false));
+        assert_eq!(results[0].detail, "2 pools offline");
+        assert_eq!(client.status(), Err(STATUS_EINVAL));
+
+        assert_eq!(HealthClient::new(Silent).check(), Err(STATUS_EIO));
+    }
+}
@@ -0,0 +1,36 @@
+/*
+ * Orion Operating System - Server Health
+ *
+ * Liveness and readiness of the system servers. Each server registers
+ * its checks in a HealthChecks (the file system server that its root is
+ * mounted, the network server that an interface is up, the storage
+ * driver that its pools are online) and answers the CHECK requests the
+ * health server sends it. The health server aggregates the answers into
+ * a HealthReport, which the metrics exporter serves over HTTP and init
+ * reads to restart the servers that stopped being live.
+ *
+ * A failing liveness check makes its server unhealthy: it has to be
+ * restarted. A failing readiness check makes it not ready: it runs, but
+ * should not be sent work yet.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod checks;
+pub mod client;
+pub mod protocol;
+
+pub use checks::{is_check, CheckFn, HealthChecks};
+pub use client::{HealthClient, Transport};
+pub use protocol::{
+    CheckResult, HealthReport, HealthState, Probe, ServerHealth, HEALTH_OP_CHECK, HEALTH_OP_REGISTER, HEALTH_OP_STATUS,
+    HEALTH_OP_UNREGISTER,
+};
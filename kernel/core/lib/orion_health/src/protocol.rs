@@ -0,0 +1,288 @@
+/*
+ * Orion Operating System - Health Protocol
+ *
+ * Wire format of the health requests. All fields are little-endian;
+ * every request starts with a 32-bit opcode and every reply with a 32-bit
+ * signed status (0 or a negative errno). Strings are `len: u32` followed
+ * by UTF-8 bytes.
+ *
+ * Every server taking part answers CHECK on its own channel; its opcode
+ * is clear of the servers' own requests so they can tell it apart before
+ * decoding anything else:
+ *
+ *   CHECK                  -> count:u32 {check}*
+ *
+ * A check is probe:u8 passed:u8 name detail, probe 1 for liveness and 2
+ * for readiness; detail says why a failed check failed.
+ *
+ * The health server answers on its "health" channel:
+ *
+ *   STATUS                 -> state:u32 count:u32 {server}*
+ *   REGISTER    name       -> (empty)
+ *   UNREGISTER  name       -> (empty)
+ *
+ * A server is name state:u32 failures:u32 count:u32 {check}*, failures
+ * being the consecutive polls it was found unhealthy. States are 0
+ * healthy, 1 not ready and 2 unhealthy.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Request every server taking part answers on its own channel ("HE" 1)
+pub const HEALTH_OP_CHECK: u32 = 0x4845_0001;
+
+// Health server opcodes
+pub const HEALTH_OP_STATUS: u32 = 1;
+pub const HEALTH_OP_REGISTER: u32 = 2;
+pub const HEALTH_OP_UNREGISTER: u32 = 3;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+/// What a failing check says about its server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// The server is stuck and has to be restarted
+    Liveness = 1,
+    /// The server runs but cannot serve requests yet, or not any more
+    Readiness = 2,
+}
+
+impl Probe {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Probe::Liveness),
+            2 => Some(Probe::Readiness),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Probe::Liveness => "liveness",
+            Probe::Readiness => "readiness",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub probe: Probe,
+    pub name: String,
+    pub passed: bool,
+    /// Why the check failed, empty when it passed
+    pub detail: String,
+}
+
+/// Ordered from best to worst, so the state of a whole is the worst of
+/// its parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthState {
+    Healthy = 0,
+    NotReady = 1,
+    Unhealthy = 2,
+}
+
+impl HealthState {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(HealthState::Healthy),
+            1 => Some(HealthState::NotReady),
+            2 => Some(HealthState::Unhealthy),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HealthState::Healthy => "healthy",
+            HealthState::NotReady => "not-ready",
+            HealthState::Unhealthy => "unhealthy",
+        }
+    }
+
+    /// State of a server given its checks: unhealthy when a liveness
+    /// check failed, not ready when a readiness check did
+    pub fn of_checks(checks: &[CheckResult]) -> Self {
+        checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| match check.probe {
+                Probe::Liveness => HealthState::Unhealthy,
+                Probe::Readiness => HealthState::NotReady,
+            })
+            .max()
+            .unwrap_or(HealthState::Healthy)
+    }
+
+    pub fn live(self) -> bool {
+        self != HealthState::Unhealthy
+    }
+
+    pub fn ready(self) -> bool {
+        self == HealthState::Healthy
+    }
+}
+
+/// Last known health of one server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerHealth {
+    pub name: String,
+    pub state: HealthState,
+    /// Consecutive polls the server was found unhealthy
+    pub failures: u32,
+    pub checks: Vec<CheckResult>,
+}
+
+/// Health of the whole system, as the health server sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    /// Worst state of the servers
+    pub state: HealthState,
+    pub servers: Vec<ServerHealth>,
+}
+
+impl HealthReport {
+    pub fn new(servers: Vec<ServerHealth>) -> Self {
+        let state = servers.iter().map(|server| server.state).max().unwrap_or(HealthState::Healthy);
+        Self { state, servers }
+    }
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decode a `len, utf-8 bytes` field and return it with the offset after it
+pub fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..(offset + 4).checked_add(len)?)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + len))
+}
+
+pub fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+pub fn encode_checks(checks: &[CheckResult], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(checks.len() as u32).to_le_bytes());
+    for check in checks {
+        out.extend_from_slice(&[check.probe as u8, check.passed as u8]);
+        put_string(out, &check.name);
+        put_string(out, &check.detail);
+    }
+}
+
+/// Decode the checks at `offset` and return them with the offset after
+/// them
+pub fn decode_checks(data: &[u8], offset: usize) -> Option<(Vec<CheckResult>, usize)> {
+    let count = read_u32(data, offset)?;
+    let mut offset = offset + 4;
+    let mut checks = Vec::new();
+    for _ in 0..count {
+        let probe = Probe::from_u8(*data.get(offset)?)?;
+        let passed = *data.get(offset + 1)? != 0;
+        let (name, next) = read_string(data, offset + 2)?;
+        let (detail, next) = read_string(data, next)?;
+        checks.push(CheckResult { probe, name, passed, detail });
+        offset = next;
+    }
+    Some((checks, offset))
+}
+
+pub fn encode_report(report: &HealthReport, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(report.state as u32).to_le_bytes());
+    out.extend_from_slice(&(report.servers.len() as u32).to_le_bytes());
+    for server in &report.servers {
+        put_string(out, &server.name);
+        out.extend_from_slice(&(server.state as u32).to_le_bytes());
+        out.extend_from_slice(&server.failures.to_le_bytes());
+        encode_checks(&server.checks, out);
+    }
+}
+
+pub fn decode_report(data: &[u8]) -> Option<HealthReport> {
+    let state = HealthState::from_u32(read_u32(data, 0)?)?;
+    let count = read_u32(data, 4)?;
+    let mut offset = 8;
+    let mut servers = Vec::new();
+    for _ in 0..count {
+        let (name, next) = read_string(data, offset)?;
+        let server_state = HealthState::from_u32(read_u32(data, next)?)?;
+        let failures = read_u32(data, next + 4)?;
+        let (checks, next) = decode_checks(data, next + 8)?;
+        servers.push(ServerHealth { name, state: server_state, failures, checks });
+        offset = next;
+    }
+    Some(HealthReport { state, servers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn check(probe: Probe, name: &str, detail: Option<&str>) -> CheckResult {
+        CheckResult {
+            probe,
+            name: String::from(name),
+            passed: detail.is_none(),
+            detail: String::from(detail.unwrap_or("")),
+        }
+    }
+
+    #[test]
+    fn test_states_follow_the_worst_failure() {
+        let ready = check(Probe::Readiness, "root", None);
+        let offline = check(Probe::Readiness, "pools", Some("vg0 offline"));
+        let stuck = check(Probe::Liveness, "stack", Some("not initialized"));
+        assert_eq!(HealthState::of_checks(&[]), HealthState::Healthy);
+        assert_eq!(HealthState::of_checks(core::slice::from_ref(&ready)), HealthState::Healthy);
+        assert_eq!(HealthState::of_checks(&[ready, offline.clone()]), HealthState::NotReady);
+        assert_eq!(HealthState::of_checks(&[stuck, offline]), HealthState::Unhealthy);
+        assert!(HealthState::NotReady.live() && !HealthState::NotReady.ready());
+    }
+
+    #[test]
+    fn test_reports_round_trip() {
+        let servers = vec![
+            ServerHealth {
+                name: String::from("fs"),
+                state: HealthState::Healthy,
+                failures: 0,
+                checks: vec![check(Probe::Readiness, "root", None)],
+            },
+            ServerHealth {
+                name: String::from("lvm-advanced"),
+                state: HealthState::NotReady,
+                failures: 0,
+                checks: vec![
+                    check(Probe::Liveness, "driver", None),
+                    check(Probe::Readiness, "pools", Some("vg0 failed")),
+                ],
+            },
+        ];
+        let report = HealthReport::new(servers);
+        assert_eq!(report.state, HealthState::NotReady);
+
+        let mut data = Vec::new();
+        encode_report(&report, &mut data);
+        assert_eq!(decode_report(&data), Some(report));
+        assert_eq!(decode_report(&data[..data.len() - 1]), None);
+        assert_eq!(HealthReport::new(Vec::new()).state, HealthState::Healthy);
+    }
+}
@@ -0,0 +1,401 @@
+/*
+ * Orion Operating System - JSON Bodies
+ *
+ * Minimal JSON support for REST handlers: a parser producing a Value tree
+ * for request bodies and a writer for response documents. Numbers are
+ * integers only, which covers every field of the management APIs; the
+ * parser refuses fractions and exponents rather than rounding them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Deepest nesting of arrays and objects accepted by the parser
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonError {
+    /// Malformed document, with the byte offset of the problem
+    Syntax(usize),
+    TooDeep,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn parse(text: &[u8]) -> Result<Value, JsonError> {
+        let mut parser = Parser { text, offset: 0 };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.offset != text.len() {
+            return Err(JsonError::Syntax(parser.offset));
+        }
+        Ok(value)
+    }
+
+    /// Member of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|value| u64::try_from(value).ok())
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self) -> Result<T, JsonError> {
+        Err(JsonError::Syntax(self.offset))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.text.get(self.offset), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.offset).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        if self.peek() != Some(byte) {
+            return self.error();
+        }
+        self.offset += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &[u8], value: Value) -> Result<Value, JsonError> {
+        if !self.text[self.offset..].starts_with(word) {
+            return self.error();
+        }
+        self.offset += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        match self.peek() {
+            Some(b'n') => self.literal(b"null", Value::Null),
+            Some(b't') => self.literal(b"true", Value::Bool(true)),
+            Some(b'f') => self.literal(b"false", Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'[') => {
+                self.offset += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.offset += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        Some(b']') => {
+                            self.offset += 1;
+                            return Ok(Value::Array(items));
+                        }
+                        _ => return self.error(),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.offset += 1;
+                let mut members = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.offset += 1;
+                    return Ok(Value::Object(members));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return self.error();
+                    }
+                    let name = self.string()?;
+                    self.expect(b':')?;
+                    members.push((name, self.value(depth + 1)?));
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        Some(b'}') => {
+                            self.offset += 1;
+                            return Ok(Value::Object(members));
+                        }
+                        _ => return self.error(),
+                    }
+                }
+            }
+            _ => self.error(),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.offset;
+        if self.text[self.offset] == b'-' {
+            self.offset += 1;
+        }
+        let digits = self.offset;
+        while matches!(self.text.get(self.offset), Some(b'0'..=b'9')) {
+            self.offset += 1;
+        }
+        let count = self.offset - digits;
+        // No leading zeros, and no fraction or exponent
+        if count == 0 || (count > 1 && self.text[digits] == b'0') {
+            return self.error();
+        }
+        if matches!(self.text.get(self.offset), Some(b'.' | b'e' | b'E')) {
+            return self.error();
+        }
+        core::str::from_utf8(&self.text[start..self.offset])
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .map(Value::Number)
+            .ok_or(JsonError::Syntax(start))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self.text.get(self.offset..self.offset + 4).ok_or(JsonError::Syntax(self.offset))?;
+        let text = core::str::from_utf8(digits).map_err(|_| JsonError::Syntax(self.offset))?;
+        let value = u32::from_str_radix(text, 16).map_err(|_| JsonError::Syntax(self.offset))?;
+        self.offset += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.offset += 1;
+        let mut out = Vec::new();
+        loop {
+            let byte = match self.text.get(self.offset) {
+                Some(byte) => *byte,
+                None => return self.error(),
+            };
+            self.offset += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.text.get(self.offset).copied();
+                    self.offset += 1;
+                    let decoded = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let mut code = self.hex4()?;
+                            if (0xD800..0xDC00).contains(&code) {
+                                // High surrogate: must be followed by \uDC00-\uDFFF
+                                if !self.text[self.offset..].starts_with(b"\\u") {
+                                    return self.error();
+                                }
+                                self.offset += 2;
+                                let low = self.hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return self.error();
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            match char::from_u32(code) {
+                                Some(decoded) => decoded,
+                                None => return self.error(),
+                            }
+                        }
+                        _ => return self.error(),
+                    };
+                    let mut buffer = [0u8; 4];
+                    out.extend_from_slice(decoded.encode_utf8(&mut buffer).as_bytes());
+                }
+                0x00..=0x1f => return self.error(),
+                _ => out.push(byte),
+            }
+        }
+        String::from_utf8(out).map_err(|_| JsonError::Syntax(self.offset))
+    }
+}
+
+/// Append `value` as a JSON string literal
+pub fn escape_into(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Builder for a JSON object, members in insertion order
+pub struct ObjectWriter {
+    out: String,
+    empty: bool,
+}
+
+impl Default for ObjectWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ObjectWriter {
+    pub fn new() -> Self {
+        Self {
+            out: String::from("{"),
+            empty: true,
+        }
+    }
+
+    fn key(&mut self, name: &str) -> &mut String {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+        escape_into(&mut self.out, name);
+        self.out.push(':');
+        &mut self.out
+    }
+
+    pub fn string(mut self, name: &str, value: &str) -> Self {
+        escape_into(self.key(name), value);
+        self
+    }
+
+    pub fn number(mut self, name: &str, value: i64) -> Self {
+        self.key(name).push_str(&format!("{}", value));
+        self
+    }
+
+    pub fn unsigned(mut self, name: &str, value: u64) -> Self {
+        self.key(name).push_str(&format!("{}", value));
+        self
+    }
+
+    pub fn boolean(mut self, name: &str, value: bool) -> Self {
+        self.key(name).push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    pub fn null(mut self, name: &str) -> Self {
+        self.key(name).push_str("null");
+        self
+    }
+
+    /// Member whose value is an already encoded JSON document
+    pub fn raw(mut self, name: &str, json: &str) -> Self {
+        self.key(name).push_str(json);
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push('}');
+        self.out
+    }
+}
+
+/// JSON array of already encoded documents
+pub fn array(items: impl Iterator<Item = String>) -> String {
+    let items: Vec<String> = items.collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_documents() {
+        let value = Value::parse(br#" {"up": true, "mtu": 9000, "name": "eth\u00e9\n", "tags": [null, -3, {}]} "#).unwrap();
+        assert_eq!(value.get("up").and_then(Value::as_bool), Some(true));
+        assert_eq!(value.get("mtu").and_then(Value::as_u64), Some(9000));
+        assert_eq!(value.get("name").and_then(Value::as_str), Some("eth\u{e9}\n"));
+        let tags = value.get("tags").and_then(Value::as_array).unwrap();
+        assert_eq!(tags[1].as_u64(), None);
+        assert_eq!(tags[2], Value::Object(Vec::new()));
+        assert_eq!(Value::parse(br#""\ud83d\ude00""#), Ok(Value::String(String::from("\u{1f600}"))));
+
+        for bad in [&b"{"[..], b"[1,]", b"01", b"1.5", b"{\"a\" 1}", b"\"\x01\"", b"true false", b"\"\\ud83d\""] {
+            assert!(Value::parse(bad).is_err());
+        }
+        let nested = [b'['; MAX_DEPTH + 2];
+        assert_eq!(Value::parse(&nested), Err(JsonError::TooDeep));
+    }
+
+    #[test]
+    fn test_writes_documents() {
+        let inner = ObjectWriter::new().string("name", "a\"b\\\u{1}").finish();
+        let json = ObjectWriter::new()
+            .unsigned("size", 42)
+            .boolean("up", false)
+            .null("driver")
+            .raw("items", &array([inner.clone(), inner].into_iter()))
+            .finish();
+        assert_eq!(
+            json,
+            r#"{"size":42,"up":false,"driver":null,"items":[{"name":"a\"b\\\u0001"},{"name":"a\"b\\\u0001"}]}"#
+        );
+        assert_eq!(Value::parse(json.as_bytes()).unwrap().get("size"), Some(&Value::Number(42)));
+        assert_eq!(array(core::iter::empty()), "[]");
+    }
+}
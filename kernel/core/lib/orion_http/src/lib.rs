@@ -0,0 +1,33 @@
+/*
+ * Orion Operating System - HTTP/1.1 Server
+ *
+ * Small HTTP/1.1 server for management endpoints (metrics exporter, health
+ * checks, remote management). Requests are routed to plain function
+ * handlers; responses are sent with a Content-Length or streamed with
+ * chunked transfer encoding, and connections are kept alive between
+ * requests. The server is transport agnostic and ships a transport for
+ * the network server's socket interface.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod json;
+pub mod request;
+pub mod response;
+pub mod router;
+pub mod server;
+pub mod socket;
+
+pub use json::{JsonError, ObjectWriter, Value};
+pub use request::{HttpError, Method, Request};
+pub use response::{Body, Response, Status};
+pub use router::{Params, Router};
+pub use server::{IoError, Listener, Server, ServerConfig, ServerStats, Transport};
@@ -0,0 +1,360 @@
+/*
+ * Orion Operating System - HTTP Request Parsing
+ *
+ * Incremental request parser: `Request::parse` is handed everything
+ * received so far and either returns a complete request with the number
+ * of bytes it used, asks for more data, or rejects the request. Bodies are
+ * delimited by Content-Length or chunked transfer encoding.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::response::Status;
+
+/// Largest request line plus headers
+pub const MAX_HEAD_SIZE: usize = 8192;
+/// Largest number of header fields
+pub const MAX_HEADERS: usize = 64;
+/// Largest request body
+pub const MAX_BODY_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+}
+
+impl Method {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "GET" => Some(Method::Get),
+            "HEAD" => Some(Method::Head),
+            "POST" => Some(Method::Post),
+            "PUT" => Some(Method::Put),
+            "DELETE" => Some(Method::Delete),
+            "PATCH" => Some(Method::Patch),
+            "OPTIONS" => Some(Method::Options),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Options => "OPTIONS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpError {
+    /// Malformed request line, header or body framing
+    BadRequest,
+    /// Request line and headers exceed MAX_HEAD_SIZE or MAX_HEADERS
+    HeadTooLarge,
+    /// Body exceeds MAX_BODY_SIZE
+    BodyTooLarge,
+    /// Unknown method or transfer coding
+    NotImplemented,
+    /// Anything but HTTP/1.0 and HTTP/1.1
+    VersionNotSupported,
+}
+
+impl HttpError {
+    pub fn status(self) -> Status {
+        match self {
+            HttpError::BadRequest => Status::BAD_REQUEST,
+            HttpError::HeadTooLarge => Status::HEADER_FIELDS_TOO_LARGE,
+            HttpError::BodyTooLarge => Status::PAYLOAD_TOO_LARGE,
+            HttpError::NotImplemented => Status::NOT_IMPLEMENTED,
+            HttpError::VersionNotSupported => Status::VERSION_NOT_SUPPORTED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    /// 0 for HTTP/1.0, 1 for HTTP/1.1
+    pub minor_version: u8,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|window| window == pattern)
+}
+
+fn is_token(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// Decode a chunked body; returns the body and the bytes used, or None if
+/// the final chunk has not arrived yet
+fn parse_chunked(data: &[u8]) -> Result<Option<(Vec<u8>, usize)>, HttpError> {
+    let mut body = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let line_end = match find(&data[offset..], b"\r\n") {
+            Some(end) => offset + end,
+            None if data.len() - offset > 64 => return Err(HttpError::BadRequest),
+            None => return Ok(None),
+        };
+        let line = core::str::from_utf8(&data[offset..line_end]).map_err(|_| HttpError::BadRequest)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        if size.is_empty() || size.len() > 8 {
+            return Err(HttpError::BadRequest);
+        }
+        let size = usize::from_str_radix(size, 16).map_err(|_| HttpError::BadRequest)?;
+        offset = line_end + 2;
+
+        if size == 0 {
+            // Trailer fields are accepted and ignored
+            loop {
+                let end = match find(&data[offset..], b"\r\n") {
+                    Some(end) => offset + end,
+                    None => return Ok(None),
+                };
+                let empty = end == offset;
+                offset = end + 2;
+                if empty {
+                    return Ok(Some((body, offset)));
+                }
+            }
+        }
+
+        if body.len() + size > MAX_BODY_SIZE {
+            return Err(HttpError::BodyTooLarge);
+        }
+        if data.len() < offset + size + 2 {
+            return Ok(None);
+        }
+        if &data[offset + size..offset + size + 2] != b"\r\n" {
+            return Err(HttpError::BadRequest);
+        }
+        body.extend_from_slice(&data[offset..offset + size]);
+        offset += size + 2;
+    }
+}
+
+impl Request {
+    /// Parse the request at the start of `data`
+    pub fn parse(data: &[u8]) -> Result<Option<(Request, usize)>, HttpError> {
+        let head_end = match find(&data[..data.len().min(MAX_HEAD_SIZE)], b"\r\n\r\n") {
+            Some(end) => end,
+            None if data.len() >= MAX_HEAD_SIZE => return Err(HttpError::HeadTooLarge),
+            None => return Ok(None),
+        };
+        let head = core::str::from_utf8(&data[..head_end]).map_err(|_| HttpError::BadRequest)?;
+        let mut lines = head.split("\r\n");
+
+        let mut parts = lines.next().unwrap_or("").split(' ');
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version), None) => (method, target, version),
+            _ => return Err(HttpError::BadRequest),
+        };
+        if !is_token(method) {
+            return Err(HttpError::BadRequest);
+        }
+        let method = Method::parse(method).ok_or(HttpError::NotImplemented)?;
+        let minor_version = match version {
+            "HTTP/1.1" => 1,
+            "HTTP/1.0" => 0,
+            _ if version.starts_with("HTTP/") => return Err(HttpError::VersionNotSupported),
+            _ => return Err(HttpError::BadRequest),
+        };
+        if !target.starts_with('/') {
+            return Err(HttpError::BadRequest);
+        }
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(String::from(query))),
+            None => (target, None),
+        };
+
+        let mut headers = Vec::new();
+        for line in lines {
+            if headers.len() == MAX_HEADERS {
+                return Err(HttpError::HeadTooLarge);
+            }
+            // Obsolete line folding is rejected (RFC 9112 section 5.2)
+            let (name, value) = line.split_once(':').ok_or(HttpError::BadRequest)?;
+            if !is_token(name) {
+                return Err(HttpError::BadRequest);
+            }
+            headers.push((String::from(name), String::from(value.trim())));
+        }
+
+        let mut request = Request {
+            method,
+            path: String::from(path),
+            query,
+            minor_version,
+            headers,
+            body: Vec::new(),
+        };
+        let body_start = head_end + 4;
+
+        let chunked = match request.header("transfer-encoding") {
+            Some(coding) if coding.eq_ignore_ascii_case("chunked") => true,
+            Some(_) => return Err(HttpError::NotImplemented),
+            None => false,
+        };
+        let mut lengths = request
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map(|(_, value)| value.as_str());
+        let content_length = match lengths.next() {
+            // Both framings at once is a request smuggling attempt
+            Some(_) if chunked => return Err(HttpError::BadRequest),
+            Some(value) => {
+                if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return Err(HttpError::BadRequest);
+                }
+                if lengths.any(|other| other != value) {
+                    return Err(HttpError::BadRequest);
+                }
+                let length: usize = value.parse().map_err(|_| HttpError::BodyTooLarge)?;
+                if length > MAX_BODY_SIZE {
+                    return Err(HttpError::BodyTooLarge);
+                }
+                length
+            }
+            None => 0,
+        };
+
+        if chunked {
+            return match parse_chunked(&data[body_start..])? {
+                Some((body, used)) => {
+                    request.body = body;
+                    Ok(Some((request, body_start + used)))
+                }
+                None => Ok(None),
+            };
+        }
+
+        if data.len() < body_start + content_length {
+            return Ok(None);
+        }
+        request.body = data[body_start..body_start + content_length].to_vec();
+        Ok(Some((request, body_start + content_length)))
+    }
+
+    /// Value of the first header named `name` (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Value of `name` in the query string, without percent-decoding
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.as_deref()?.split('&').find_map(|pair| match pair.split_once('=') {
+            Some((key, value)) if key == name => Some(value),
+            None if pair == name => Some(""),
+            _ => None,
+        })
+    }
+
+    /// Whether the client asked to reuse the connection
+    pub fn keep_alive(&self) -> bool {
+        let tokens = || {
+            self.header("connection")
+                .unwrap_or("")
+                .split(',')
+                .map(str::trim)
+        };
+        if self.minor_version >= 1 {
+            !tokens().any(|token| token.eq_ignore_ascii_case("close"))
+        } else {
+            tokens().any(|token| token.eq_ignore_ascii_case("keep-alive"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_pipelined_requests() {
+        let data = b"GET /metrics?format=text HTTP/1.1\r\nHost: orion\r\n\r\n\
+                     POST /config HTTP/1.1\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+
+        let (first, used) = Request::parse(data).unwrap().unwrap();
+        assert_eq!(first.method, Method::Get);
+        assert_eq!(first.path, "/metrics");
+        assert_eq!(first.query_param("format"), Some("text"));
+        assert_eq!(first.header("HOST"), Some("orion"));
+        assert!(first.keep_alive());
+
+        let (second, rest) = Request::parse(&data[used..]).unwrap().unwrap();
+        assert_eq!(second.body, b"hello");
+        assert!(!second.keep_alive());
+        assert_eq!(used + rest, data.len());
+
+        // Incomplete head and incomplete body both ask for more data
+        assert_eq!(Request::parse(&data[..20]), Ok(None));
+        assert_eq!(Request::parse(&data[used..data.len() - 1]), Ok(None));
+    }
+
+    #[test]
+    fn test_decodes_chunked_body() {
+        let data = b"PUT /x HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nTrailer: yes\r\n\r\nGET";
+        let (request, used) = Request::parse(data).unwrap().unwrap();
+        assert_eq!(request.body, b"Wikipedia");
+        assert_eq!(&data[used..], b"GET");
+
+        assert_eq!(Request::parse(&data[..data.len() - 10]), Ok(None));
+    }
+
+    #[test]
+    fn test_rejects_bad_requests() {
+        let parse = |text: &str| Request::parse(text.as_bytes());
+        assert_eq!(parse("GET /\r\n\r\n"), Err(HttpError::BadRequest));
+        assert_eq!(parse("BREW /pot HTTP/1.1\r\n\r\n"), Err(HttpError::NotImplemented));
+        assert_eq!(parse("GET / HTTP/2.0\r\n\r\n"), Err(HttpError::VersionNotSupported));
+        assert_eq!(parse("GET / HTTP/1.1\r\n folded\r\n\r\n"), Err(HttpError::BadRequest));
+        assert_eq!(
+            parse("POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Err(HttpError::BadRequest)
+        );
+        assert_eq!(
+            parse("POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\n"),
+            Err(HttpError::BadRequest)
+        );
+        assert_eq!(
+            parse("POST / HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n"),
+            Err(HttpError::BodyTooLarge)
+        );
+        assert_eq!(Request::parse(&[b'a'; MAX_HEAD_SIZE]), Err(HttpError::HeadTooLarge));
+
+        let http10 = parse("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").unwrap().unwrap().0;
+        assert!(http10.keep_alive());
+    }
+}
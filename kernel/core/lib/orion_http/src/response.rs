@@ -0,0 +1,156 @@
+/*
+ * Orion Operating System - HTTP Responses
+ *
+ * Response builder and wire encoding. A body is either held in memory and
+ * sent with a Content-Length, or produced piece by piece by an iterator
+ * and sent with chunked transfer encoding so that large exports (metrics,
+ * logs) never have to be rendered in one buffer.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status(pub u16);
+
+impl Status {
+    pub const OK: Status = Status(200);
+    pub const CREATED: Status = Status(201);
+    pub const NO_CONTENT: Status = Status(204);
+    pub const BAD_REQUEST: Status = Status(400);
+    pub const UNAUTHORIZED: Status = Status(401);
+    pub const FORBIDDEN: Status = Status(403);
+    pub const NOT_FOUND: Status = Status(404);
+    pub const METHOD_NOT_ALLOWED: Status = Status(405);
+    pub const CONFLICT: Status = Status(409);
+    pub const PAYLOAD_TOO_LARGE: Status = Status(413);
+    pub const HEADER_FIELDS_TOO_LARGE: Status = Status(431);
+    pub const INTERNAL_SERVER_ERROR: Status = Status(500);
+    pub const NOT_IMPLEMENTED: Status = Status(501);
+    pub const SERVICE_UNAVAILABLE: Status = Status(503);
+    pub const VERSION_NOT_SUPPORTED: Status = Status(505);
+
+    pub fn reason(self) -> &'static str {
+        match self.0 {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Content Too Large",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            503 => "Service Unavailable",
+            505 => "HTTP Version Not Supported",
+            _ => "",
+        }
+    }
+
+    /// 1xx, 204 and 304 responses never carry a body
+    fn allows_body(self) -> bool {
+        self.0 >= 200 && self.0 != 204 && self.0 != 304
+    }
+}
+
+pub enum Body {
+    Empty,
+    Full(Vec<u8>),
+    /// Streamed with chunked transfer encoding. An empty piece means nothing
+    /// is ready yet: the server tries the iterator again on its next poll.
+    Chunked(Box<dyn Iterator<Item = Vec<u8>>>),
+}
+
+pub struct Response {
+    pub status: Status,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Response {
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Body::Empty,
+        }
+    }
+
+    pub fn with_body(status: Status, content_type: &str, body: Vec<u8>) -> Self {
+        Self::new(status).header("Content-Type", content_type).body(Body::Full(body))
+    }
+
+    pub fn text(status: Status, text: &str) -> Self {
+        Self::with_body(status, "text/plain; charset=utf-8", Vec::from(text.as_bytes()))
+    }
+
+    pub fn json(status: Status, json: String) -> Self {
+        Self::with_body(status, "application/json", json.into_bytes())
+    }
+
+    pub fn chunked(status: Status, content_type: &str, pieces: impl Iterator<Item = Vec<u8>> + 'static) -> Self {
+        Self::new(status)
+            .header("Content-Type", content_type)
+            .body(Body::Chunked(Box::new(pieces)))
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Status line and header block. `chunked` selects the framing of a
+    /// streamed body; without it the body is delimited by closing the
+    /// connection (HTTP/1.0 clients).
+    pub(crate) fn encode_head(&self, keep_alive: bool, chunked: bool) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status.0, self.status.reason());
+        for (name, value) in self.headers.iter() {
+            head.push_str(name);
+            head.push_str(": ");
+            head.push_str(value);
+            head.push_str("\r\n");
+        }
+
+        if self.status.allows_body() {
+            match &self.body {
+                Body::Empty => head.push_str("Content-Length: 0\r\n"),
+                Body::Full(body) => head.push_str(&format!("Content-Length: {}\r\n", body.len())),
+                Body::Chunked(_) if chunked => head.push_str("Transfer-Encoding: chunked\r\n"),
+                Body::Chunked(_) => {}
+            }
+        }
+        head.push_str(if keep_alive { "Connection: keep-alive\r\n\r\n" } else { "Connection: close\r\n\r\n" });
+        head.into_bytes()
+    }
+
+    pub(crate) fn has_body(&self) -> bool {
+        self.status.allows_body()
+    }
+}
+
+/// Append one piece of a chunked body
+pub(crate) fn encode_chunk(piece: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("{:x}\r\n", piece.len()).as_bytes());
+    out.extend_from_slice(piece);
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Last chunk of a chunked body, without trailers
+pub(crate) const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
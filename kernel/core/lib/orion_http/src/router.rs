@@ -0,0 +1,222 @@
+/*
+ * Orion Operating System - HTTP Routing
+ *
+ * Routes map a method and a path pattern to a handler. Patterns are
+ * matched segment by segment: `:name` captures one segment and a trailing
+ * `*name` captures the rest of the path. HEAD requests fall back to the
+ * GET route of the same path.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::request::{Method, Request};
+use crate::response::{Response, Status};
+
+/// Route handler; `S` is the state of the server embedding the router
+pub type Handler<S> = fn(&mut S, &Request, &Params) -> Response;
+
+/// Path segments captured by a route pattern
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Params {
+    entries: Vec<(String, String)>,
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest(String),
+}
+
+struct Route<S> {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Handler<S>,
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+impl<S> Route<S> {
+    fn matches(&self, path: &str) -> Option<Params> {
+        let mut params = Params::default();
+        let mut parts = split_path(path);
+
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.entries.push((name.clone(), String::from(parts.next()?)));
+                }
+                Segment::Rest(name) => {
+                    let rest: Vec<&str> = parts.by_ref().collect();
+                    params.entries.push((name.clone(), rest.join("/")));
+                }
+            }
+        }
+
+        match parts.next() {
+            Some(_) => None,
+            None => Some(params),
+        }
+    }
+}
+
+pub struct Router<S> {
+    routes: Vec<Route<S>>,
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Router<S> {
+    pub fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    pub fn route(mut self, method: Method, pattern: &str, handler: Handler<S>) -> Self {
+        let segments = split_path(pattern)
+            .map(|segment| {
+                if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Param(String::from(name))
+                } else if let Some(name) = segment.strip_prefix('*') {
+                    Segment::Rest(String::from(name))
+                } else {
+                    Segment::Literal(String::from(segment))
+                }
+            })
+            .collect();
+        self.routes.push(Route { method, segments, handler });
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: Handler<S>) -> Self {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post(self, pattern: &str, handler: Handler<S>) -> Self {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    pub fn put(self, pattern: &str, handler: Handler<S>) -> Self {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    pub fn delete(self, pattern: &str, handler: Handler<S>) -> Self {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    /// Run the handler matching `request`, or answer 404/405
+    pub fn dispatch(&self, state: &mut S, request: &Request) -> Response {
+        let mut allowed: Vec<Method> = Vec::new();
+        let mut fallback = None;
+
+        for route in self.routes.iter() {
+            let params = match route.matches(&request.path) {
+                Some(params) => params,
+                None => continue,
+            };
+            if route.method == request.method {
+                return (route.handler)(state, request, &params);
+            }
+            if request.method == Method::Head && route.method == Method::Get && fallback.is_none() {
+                fallback = Some((route.handler, params));
+            }
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method);
+            }
+        }
+
+        if let Some((handler, params)) = fallback {
+            return handler(state, request, &params);
+        }
+        if allowed.is_empty() {
+            return Response::text(Status::NOT_FOUND, "not found\n");
+        }
+
+        let allow: Vec<&str> = allowed.iter().map(|method| method.as_str()).collect();
+        Response::text(Status::METHOD_NOT_ALLOWED, "method not allowed\n").header("Allow", &allow.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Body;
+
+    fn request(method: Method, path: &str) -> Request {
+        Request {
+            method,
+            path: String::from(path),
+            query: None,
+            minor_version: 1,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn echo(calls: &mut u32, _request: &Request, params: &Params) -> Response {
+        *calls += 1;
+        let text = alloc::format!("{}|{}", params.get("pool").unwrap_or(""), params.get("path").unwrap_or(""));
+        Response::text(Status::OK, &text)
+    }
+
+    fn body(response: &Response) -> &[u8] {
+        match &response.body {
+            Body::Full(body) => body,
+            _ => &[],
+        }
+    }
+
+    #[test]
+    fn test_dispatches_routes() {
+        let router = Router::new()
+            .get("/pools/:pool", echo)
+            .delete("/pools/:pool", echo)
+            .get("/files/*path", echo);
+        let mut calls = 0;
+
+        let response = router.dispatch(&mut calls, &request(Method::Get, "/pools/tank"));
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(body(&response), b"tank|");
+
+        let response = router.dispatch(&mut calls, &request(Method::Get, "/files/etc/orion/net.conf"));
+        assert_eq!(body(&response), b"|etc/orion/net.conf");
+
+        // HEAD is served by the GET handler
+        let response = router.dispatch(&mut calls, &request(Method::Head, "/pools/tank"));
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(calls, 3);
+
+        let response = router.dispatch(&mut calls, &request(Method::Put, "/pools/tank"));
+        assert_eq!(response.status, Status::METHOD_NOT_ALLOWED);
+        assert!(response.headers.contains(&(String::from("Allow"), String::from("GET, DELETE"))));
+
+        let response = router.dispatch(&mut calls, &request(Method::Get, "/pools/tank/snapshots"));
+        assert_eq!(response.status, Status::NOT_FOUND);
+        assert_eq!(calls, 3);
+    }
+}
@@ -0,0 +1,402 @@
+/*
+ * Orion Operating System - HTTP Server
+ *
+ * Poll-driven server: every call to `Server::poll` accepts pending
+ * connections and moves each of them forward without blocking. A
+ * connection reads and parses requests, runs the router and queues the
+ * responses; pipelined requests are answered in order and a streamed body
+ * is finished before the next request is looked at. Connections stay
+ * open until the client asks otherwise or the per-connection request
+ * budget runs out.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::request::{HttpError, Method, Request, MAX_BODY_SIZE, MAX_HEAD_SIZE};
+use crate::response::{encode_chunk, Body, Response, LAST_CHUNK};
+use crate::router::Router;
+
+/// Bytes requested from the transport per read
+const READ_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoError {
+    /// Nothing can be transferred right now
+    WouldBlock,
+    /// The peer went away or the socket failed
+    Closed,
+}
+
+/// Byte stream carrying one HTTP connection
+pub trait Transport {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError>;
+    fn write(&mut self, data: &[u8]) -> Result<usize, IoError>;
+    fn close(&mut self);
+}
+
+/// Source of new connections
+pub trait Listener {
+    type Stream: Transport;
+
+    fn accept(&mut self) -> Result<Self::Stream, IoError>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServerConfig {
+    pub max_connections: usize,
+    /// Requests served on one connection before it is closed
+    pub max_requests_per_connection: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 32,
+            max_requests_per_connection: 100,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServerStats {
+    pub connections_accepted: u64,
+    pub connections_rejected: u64,
+    pub requests: u64,
+    /// Requests that could not be parsed
+    pub bad_requests: u64,
+}
+
+struct Connection<T: Transport> {
+    transport: T,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+    stream: Option<Box<dyn Iterator<Item = Vec<u8>>>>,
+    stream_chunked: bool,
+    requests: u32,
+    close_after_response: bool,
+    peer_closed: bool,
+}
+
+impl<T: Transport> Connection<T> {
+    fn new(transport: T) -> Self {
+        Self {
+            transport,
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+            stream: None,
+            stream_chunked: false,
+            requests: 0,
+            close_after_response: false,
+            peer_closed: false,
+        }
+    }
+
+    fn receive(&mut self) {
+        let mut buffer = vec![0u8; READ_SIZE];
+        while !self.peer_closed && self.inbound.len() < MAX_HEAD_SIZE + MAX_BODY_SIZE {
+            match self.transport.read(&mut buffer) {
+                Ok(0) | Err(IoError::WouldBlock) => break,
+                Ok(count) => self.inbound.extend_from_slice(&buffer[..count]),
+                Err(IoError::Closed) => self.peer_closed = true,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        while !self.outbound.is_empty() {
+            match self.transport.write(&self.outbound) {
+                Ok(0) | Err(IoError::WouldBlock) => break,
+                Ok(count) => {
+                    self.outbound.drain(..count);
+                }
+                Err(IoError::Closed) => return Err(IoError::Closed),
+            }
+        }
+        Ok(())
+    }
+
+    fn queue_response(&mut self, response: Response, keep_alive: bool, minor_version: u8, head_only: bool) {
+        // HTTP/1.0 has no chunked coding: the end of the connection ends the body
+        let chunked = minor_version >= 1;
+        let streamed = matches!(response.body, Body::Chunked(_));
+        let keep_alive = keep_alive && (chunked || !streamed);
+
+        self.outbound.extend_from_slice(&response.encode_head(keep_alive, chunked));
+        if response.has_body() && !head_only {
+            match response.body {
+                Body::Empty => {}
+                Body::Full(body) => self.outbound.extend_from_slice(&body),
+                Body::Chunked(pieces) => {
+                    self.stream = Some(pieces);
+                    self.stream_chunked = chunked;
+                }
+            }
+        }
+        self.close_after_response = !keep_alive;
+    }
+
+    fn respond<S>(&mut self, router: &Router<S>, state: &mut S, config: &ServerConfig, request: Request) {
+        self.requests += 1;
+        let keep_alive = request.keep_alive() && self.requests < config.max_requests_per_connection;
+        let response = router.dispatch(state, &request);
+        self.queue_response(response, keep_alive, request.minor_version, request.method == Method::Head);
+    }
+
+    fn reject(&mut self, error: HttpError) {
+        self.inbound.clear();
+        let response = Response::text(error.status(), error.status().reason());
+        self.queue_response(response, false, 1, false);
+    }
+
+    /// Make progress; returns false once the connection is finished
+    fn poll<S>(&mut self, router: &Router<S>, state: &mut S, config: &ServerConfig, stats: &mut ServerStats) -> bool {
+        self.receive();
+
+        loop {
+            if self.flush().is_err() {
+                break;
+            }
+            if !self.outbound.is_empty() {
+                // Wait for the peer to drain what is queued
+                return true;
+            }
+
+            if let Some(pieces) = self.stream.as_mut() {
+                match pieces.next() {
+                    // Nothing ready yet (event streams): try again next poll
+                    Some(piece) if piece.is_empty() => {
+                        if self.peer_closed {
+                            break;
+                        }
+                        return true;
+                    }
+                    Some(piece) if self.stream_chunked => encode_chunk(&piece, &mut self.outbound),
+                    Some(piece) => self.outbound.extend_from_slice(&piece),
+                    None => {
+                        if self.stream_chunked {
+                            self.outbound.extend_from_slice(LAST_CHUNK);
+                        }
+                        self.stream = None;
+                    }
+                }
+                continue;
+            }
+
+            if self.close_after_response {
+                break;
+            }
+
+            match Request::parse(&self.inbound) {
+                Ok(Some((request, used))) => {
+                    self.inbound.drain(..used);
+                    stats.requests += 1;
+                    self.respond(router, state, config, request);
+                }
+                Ok(None) if self.peer_closed => break,
+                Ok(None) => return true,
+                Err(error) => {
+                    stats.bad_requests += 1;
+                    self.reject(error);
+                }
+            }
+        }
+
+        self.transport.close();
+        false
+    }
+}
+
+pub struct Server<L: Listener> {
+    listener: L,
+    connections: Vec<Connection<L::Stream>>,
+    config: ServerConfig,
+    stats: ServerStats,
+}
+
+impl<L: Listener> Server<L> {
+    pub fn new(listener: L, config: ServerConfig) -> Self {
+        Self {
+            listener,
+            connections: Vec::new(),
+            config,
+            stats: ServerStats::default(),
+        }
+    }
+
+    /// Accept new connections and serve every open one once
+    pub fn poll<S>(&mut self, router: &Router<S>, state: &mut S) {
+        while let Ok(mut stream) = self.listener.accept() {
+            if self.connections.len() >= self.config.max_connections {
+                self.stats.connections_rejected += 1;
+                stream.close();
+                continue;
+            }
+            self.stats.connections_accepted += 1;
+            self.connections.push(Connection::new(stream));
+        }
+
+        let config = self.config;
+        let stats = &mut self.stats;
+        self.connections
+            .retain_mut(|connection| connection.poll(router, state, &config, stats));
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn stats(&self) -> ServerStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Status;
+    use crate::router::Params;
+    use alloc::rc::Rc;
+    use alloc::string::String;
+    use core::cell::RefCell;
+
+    /// In-memory transport: `input` is what the client sent, `output` what
+    /// the server wrote; writes accept at most `write_limit` bytes per call
+    #[derive(Default)]
+    struct Pipe {
+        input: Vec<u8>,
+        output: Vec<u8>,
+        client_closed: bool,
+        server_closed: bool,
+        write_limit: usize,
+    }
+
+    #[derive(Clone)]
+    struct Stream(Rc<RefCell<Pipe>>);
+
+    impl Transport for Stream {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+            let mut pipe = self.0.borrow_mut();
+            if pipe.input.is_empty() {
+                return Err(if pipe.client_closed { IoError::Closed } else { IoError::WouldBlock });
+            }
+            let count = buffer.len().min(pipe.input.len());
+            buffer[..count].copy_from_slice(&pipe.input[..count]);
+            pipe.input.drain(..count);
+            Ok(count)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+            let mut pipe = self.0.borrow_mut();
+            let count = data.len().min(pipe.write_limit.max(1));
+            pipe.output.extend_from_slice(&data[..count]);
+            Ok(count)
+        }
+
+        fn close(&mut self) {
+            self.0.borrow_mut().server_closed = true;
+        }
+    }
+
+    struct Pending(Vec<Stream>);
+
+    impl Listener for Pending {
+        type Stream = Stream;
+
+        fn accept(&mut self) -> Result<Stream, IoError> {
+            self.0.pop().ok_or(IoError::WouldBlock)
+        }
+    }
+
+    fn connect(input: &str, write_limit: usize) -> (Stream, Server<Pending>) {
+        let pipe = Stream(Rc::new(RefCell::new(Pipe {
+            input: Vec::from(input.as_bytes()),
+            write_limit,
+            ..Default::default()
+        })));
+        let server = Server::new(Pending(vec![pipe.clone()]), ServerConfig::default());
+        (pipe, server)
+    }
+
+    fn output(stream: &Stream) -> String {
+        String::from_utf8(stream.0.borrow().output.clone()).unwrap()
+    }
+
+    fn health(_state: &mut u32, _request: &Request, _params: &Params) -> Response {
+        Response::text(Status::OK, "ok\n")
+    }
+
+    fn metrics(state: &mut u32, _request: &Request, _params: &Params) -> Response {
+        *state += 1;
+        let pieces = vec![Vec::from(&b"a 1\n"[..]), Vec::new(), Vec::from(&b"b 22\n"[..])];
+        Response::chunked(Status::OK, "text/plain", pieces.into_iter())
+    }
+
+    fn router() -> Router<u32> {
+        Router::new().get("/healthz", health).get("/metrics", metrics)
+    }
+
+    #[test]
+    fn test_keeps_connection_alive() {
+        let (stream, mut server) = connect(
+            "GET /healthz HTTP/1.1\r\n\r\nGET /metrics HTTP/1.1\r\n\r\nGET /nope HTTP/1.1\r\n\r\n",
+            7,
+        );
+        let mut state = 0;
+        let router = router();
+
+        for _ in 0..64 {
+            server.poll(&router, &mut state);
+        }
+        assert_eq!(
+            output(&stream),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 3\r\n\
+             Connection: keep-alive\r\n\r\nok\n\
+             HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\
+             Connection: keep-alive\r\n\r\n4\r\na 1\n\r\n5\r\nb 22\n\r\n0\r\n\r\n\
+             HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 10\r\n\
+             Connection: keep-alive\r\n\r\nnot found\n"
+        );
+        assert_eq!(state, 1);
+        assert_eq!(server.connection_count(), 1);
+        assert_eq!(server.stats().requests, 3);
+
+        // The client hanging up ends the connection
+        stream.0.borrow_mut().client_closed = true;
+        server.poll(&router, &mut state);
+        assert_eq!(server.connection_count(), 0);
+        assert!(stream.0.borrow().server_closed);
+    }
+
+    #[test]
+    fn test_closes_when_asked() {
+        let (stream, mut server) = connect("HEAD /metrics HTTP/1.0\r\n\r\n", 4096);
+        let mut state = 0;
+        server.poll(&router(), &mut state);
+        assert_eq!(
+            output(&stream),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\n"
+        );
+        assert!(stream.0.borrow().server_closed);
+
+        // The empty piece holds the rest of the body until the next poll
+        let (stream, mut server) = connect("GET /metrics HTTP/1.0\r\n\r\n", 4096);
+        server.poll(&router(), &mut state);
+        assert!(output(&stream).ends_with("Connection: close\r\n\r\na 1\n"));
+        server.poll(&router(), &mut state);
+        assert!(output(&stream).ends_with("Connection: close\r\n\r\na 1\nb 22\n"));
+
+        let (stream, mut server) = connect("GET /healthz HTTP/1.1\r\nContent-Length: x\r\n\r\n", 4096);
+        server.poll(&router(), &mut state);
+        assert!(output(&stream).starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(stream.0.borrow().server_closed);
+        assert_eq!(server.stats().bad_requests, 1);
+    }
+}
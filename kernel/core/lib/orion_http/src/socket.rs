@@ -0,0 +1,226 @@
+/*
+ * Orion Operating System - Network Server Sockets
+ *
+ * Transport over the socket interface of the network server (see
+ * services/net/socket_ipc.h). Each operation is one request/reply call;
+ * ACCEPT and RECV answer -EAGAIN instead of blocking, which is what the
+ * poll-driven server expects. Listeners configured with `enable_tls` and
+ * streams upgraded with `start_tls` have TLS terminated by the network
+ * server and still carry plaintext here, as do outgoing streams opened
+ * with `connect_tls`. `tls_exporter` gives keying material bound to the
+ * TLS session of a stream, for protocols that sign something of their
+ * own inside it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::server::{IoError, Listener, Transport};
+
+// Opcodes
+pub const OP_LISTEN: u32 = 1;
+pub const OP_LISTEN_TLS: u32 = 2;
+pub const OP_ACCEPT: u32 = 3;
+pub const OP_SEND: u32 = 4;
+pub const OP_RECV: u32 = 5;
+pub const OP_CLOSE: u32 = 6;
+pub const OP_START_TLS: u32 = 10;
+pub const OP_CONNECT: u32 = 12;
+pub const OP_CONNECT_TLS: u32 = 13;
+pub const OP_TLS_EXPORT: u32 = 15;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EAGAIN: i32 = -11;
+
+/// Largest SEND/RECV payload accepted by the network server
+pub const MAX_TRANSFER: usize = 8192;
+
+/// Request/reply channel to the network server, usually an IpcChannel
+/// connected to "net"
+pub trait NetChannel {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+type SharedChannel<C> = Rc<RefCell<C>>;
+
+/// Issue one request and split the reply into status and payload
+fn request<C: NetChannel>(channel: &SharedChannel<C>, op: u32, socket: u32, args: &[u8]) -> Result<Vec<u8>, i32> {
+    let mut message = Vec::with_capacity(8 + args.len());
+    message.extend_from_slice(&op.to_le_bytes());
+    message.extend_from_slice(&socket.to_le_bytes());
+    message.extend_from_slice(args);
+
+    let reply = channel.borrow_mut().call(&message).ok_or(STATUS_EIO)?;
+    if reply.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]]) {
+        STATUS_OK => Ok(reply[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn read_u32(payload: &[u8]) -> Result<u32, i32> {
+    let bytes = payload.get(..4).ok_or(STATUS_EIO)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub struct NetListener<C: NetChannel> {
+    channel: SharedChannel<C>,
+    socket: u32,
+}
+
+impl<C: NetChannel> NetListener<C> {
+    /// Listen on `ip:port` (ip 0 for every interface)
+    pub fn bind(channel: C, ip: u32, port: u16, backlog: u16) -> Result<Self, i32> {
+        let channel = Rc::new(RefCell::new(channel));
+        // LISTEN carries the address where other requests carry a socket
+        let mut args = Vec::with_capacity(4);
+        args.extend_from_slice(&port.to_le_bytes());
+        args.extend_from_slice(&backlog.to_le_bytes());
+        let payload = request(&channel, OP_LISTEN, ip, &args)?;
+        Ok(Self {
+            socket: read_u32(&payload)?,
+            channel,
+        })
+    }
+
+    /// Terminate TLS for accepted connections with keyring-held credentials
+    pub fn enable_tls(&self, certificate_handle: u64, key_handle: u64) -> Result<(), i32> {
+        let mut args = Vec::with_capacity(16);
+        args.extend_from_slice(&certificate_handle.to_le_bytes());
+        args.extend_from_slice(&key_handle.to_le_bytes());
+        request(&self.channel, OP_LISTEN_TLS, self.socket, &args).map(|_| ())
+    }
+}
+
+impl<C: NetChannel> Listener for NetListener<C> {
+    type Stream = NetStream<C>;
+
+    fn accept(&mut self) -> Result<NetStream<C>, IoError> {
+        match request(&self.channel, OP_ACCEPT, self.socket, &[]) {
+            Ok(payload) => Ok(NetStream {
+                channel: self.channel.clone(),
+                socket: read_u32(&payload).map_err(|_| IoError::Closed)?,
+                open: true,
+            }),
+            Err(STATUS_EAGAIN) => Err(IoError::WouldBlock),
+            Err(_) => Err(IoError::Closed),
+        }
+    }
+}
+
+impl<C: NetChannel> Drop for NetListener<C> {
+    fn drop(&mut self) {
+        let _ = request(&self.channel, OP_CLOSE, self.socket, &[]);
+    }
+}
+
+pub struct NetStream<C: NetChannel> {
+    channel: SharedChannel<C>,
+    socket: u32,
+    open: bool,
+}
+
+impl<C: NetChannel> NetStream<C> {
+    /// Open a stream to `ip:port`; reads and writes would block until the
+    /// connection is established
+    pub fn connect(channel: C, ip: u32, port: u16) -> Result<Self, i32> {
+        Self::open(channel, OP_CONNECT, ip, &port.to_le_bytes())
+    }
+
+    /// Open a stream to `ip:port` with the client side of TLS run by the
+    /// network server. The server must present a chain leading to the CA
+    /// certificate under keyring handle `anchor` and naming `server_name`
+    pub fn connect_tls(channel: C, ip: u32, port: u16, anchor: u64, server_name: &str) -> Result<Self, i32> {
+        let mut args = Vec::with_capacity(10 + server_name.len());
+        args.extend_from_slice(&port.to_le_bytes());
+        args.extend_from_slice(&anchor.to_le_bytes());
+        args.extend_from_slice(server_name.as_bytes());
+        Self::open(channel, OP_CONNECT_TLS, ip, &args)
+    }
+
+    // CONNECT carries the address where other requests carry a socket
+    fn open(channel: C, op: u32, ip: u32, args: &[u8]) -> Result<Self, i32> {
+        let channel = Rc::new(RefCell::new(channel));
+        let payload = request(&channel, op, ip, args)?;
+        Ok(Self {
+            socket: read_u32(&payload)?,
+            channel,
+            open: true,
+        })
+    }
+
+    /// Upgrade the stream to TLS with keyring-held credentials; everything
+    /// written before stays plaintext
+    pub fn start_tls(&self, certificate_handle: u64, key_handle: u64) -> Result<(), i32> {
+        let mut args = Vec::with_capacity(16);
+        args.extend_from_slice(&certificate_handle.to_le_bytes());
+        args.extend_from_slice(&key_handle.to_le_bytes());
+        request(&self.channel, OP_START_TLS, self.socket, &args).map(|_| ())
+    }
+
+    /// `length` bytes (at most 64) exported from the TLS session under
+    /// `label` with an empty context (RFC 8446 section 7.5); -EAGAIN while
+    /// the handshake runs
+    pub fn tls_exporter(&self, label: &str, length: u16) -> Result<Vec<u8>, i32> {
+        let mut args = Vec::with_capacity(2 + label.len());
+        args.extend_from_slice(&length.to_le_bytes());
+        args.extend_from_slice(label.as_bytes());
+        let payload = request(&self.channel, OP_TLS_EXPORT, self.socket, &args)?;
+        if payload.len() != length as usize {
+            return Err(STATUS_EIO);
+        }
+        Ok(payload)
+    }
+}
+
+impl<C: NetChannel> Transport for NetStream<C> {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+        let max = buffer.len().min(MAX_TRANSFER) as u32;
+        match request(&self.channel, OP_RECV, self.socket, &max.to_le_bytes()) {
+            Ok(payload) if payload.len() <= buffer.len() => {
+                buffer[..payload.len()].copy_from_slice(&payload);
+                Ok(payload.len())
+            }
+            Ok(_) => Err(IoError::Closed),
+            Err(STATUS_EAGAIN) => Err(IoError::WouldBlock),
+            Err(_) => Err(IoError::Closed),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+        let data = &data[..data.len().min(MAX_TRANSFER)];
+        match request(&self.channel, OP_SEND, self.socket, data) {
+            Ok(payload) => match read_u32(&payload) {
+                Ok(0) => Err(IoError::WouldBlock),
+                Ok(sent) => Ok(sent as usize),
+                Err(_) => Err(IoError::Closed),
+            },
+            Err(STATUS_EAGAIN) => Err(IoError::WouldBlock),
+            Err(_) => Err(IoError::Closed),
+        }
+    }
+
+    fn close(&mut self) {
+        if self.open {
+            self.open = false;
+            let _ = request(&self.channel, OP_CLOSE, self.socket, &[]);
+        }
+    }
+}
+
+impl<C: NetChannel> Drop for NetStream<C> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,359 @@
+/*
+ * Orion Operating System - I2C Adapters
+ *
+ * What an I2C bus driver implements. An adapter runs transfers, a list
+ * of messages sent as one transaction with a repeated start between
+ * them, and says what it can do. SMBus operations are a fixed set of
+ * such transactions: adapters that only speak SMBus, like the PC SMBus
+ * host controllers, run them in hardware, and on the others they are
+ * emulated with transfers.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::BitOr;
+
+// Message flags
+/// Read from the device rather than write to it
+pub const I2C_M_RD: u16 = 0x0001;
+/// 10-bit address
+pub const I2C_M_TEN: u16 = 0x0010;
+/// The first byte read is the number of bytes that follow, at most
+/// SMBUS_BLOCK_MAX; the adapter truncates `data` to what was read
+pub const I2C_M_RECV_LEN: u16 = 0x0400;
+
+/// Most data bytes in an SMBus block
+pub const SMBUS_BLOCK_MAX: usize = 32;
+
+/// Lowest and highest 7-bit addresses of devices; the others are
+/// reserved by the specification
+pub const I2C_ADDRESS_MIN: u16 = 0x08;
+pub const I2C_ADDRESS_MAX: u16 = 0x77;
+pub const I2C_TEN_BIT_MAX: u16 = 0x3FF;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct I2cMessage {
+    pub address: u16,
+    pub flags: u16,
+    /// Bytes to write, or a buffer the length of the read
+    pub data: Vec<u8>,
+}
+
+impl I2cMessage {
+    pub fn write(address: u16, data: &[u8]) -> Self {
+        Self { address, flags: 0, data: data.to_vec() }
+    }
+
+    pub fn read(address: u16, length: usize) -> Self {
+        Self { address, flags: I2C_M_RD, data: vec![0; length] }
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.flags & I2C_M_RD != 0
+    }
+
+    /// Address valid for the flags
+    pub fn address_valid(&self) -> bool {
+        if self.flags & I2C_M_TEN != 0 {
+            self.address <= I2C_TEN_BIT_MAX
+        } else {
+            (I2C_ADDRESS_MIN..=I2C_ADDRESS_MAX).contains(&self.address)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cError {
+    /// No device acknowledged its address, or a byte
+    NoAck,
+    /// Another master won the bus
+    ArbitrationLost,
+    Timeout,
+    BusError,
+    NotSupported,
+    InvalidArgument,
+}
+
+impl I2cError {
+    /// Reply status, a negative errno
+    pub fn status(self) -> i32 {
+        match self {
+            I2cError::NoAck => -6,
+            I2cError::ArbitrationLost => -11,
+            I2cError::Timeout => -110,
+            I2cError::BusError => -5,
+            I2cError::NotSupported => -95,
+            I2cError::InvalidArgument => -22,
+        }
+    }
+}
+
+/// What an adapter can do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Functionality(u32);
+
+impl Functionality {
+    pub const NONE: Functionality = Functionality(0);
+    /// Plain I2C transfers
+    pub const I2C: Functionality = Functionality(1 << 0);
+    pub const TEN_BIT: Functionality = Functionality(1 << 1);
+    /// Reads of I2C_M_RECV_LEN
+    pub const RECV_LEN: Functionality = Functionality(1 << 2);
+    pub const SMBUS_QUICK: Functionality = Functionality(1 << 8);
+    pub const SMBUS_BYTE: Functionality = Functionality(1 << 9);
+    pub const SMBUS_BYTE_DATA: Functionality = Functionality(1 << 10);
+    pub const SMBUS_WORD_DATA: Functionality = Functionality(1 << 11);
+    pub const SMBUS_BLOCK_DATA: Functionality = Functionality(1 << 12);
+
+    /// The SMBus operations transfers emulate; quick commands need
+    /// zero-length messages, which not every controller sends
+    pub const SMBUS_EMULATED: Functionality = Functionality(
+        Self::SMBUS_BYTE.0 | Self::SMBUS_BYTE_DATA.0 | Self::SMBUS_WORD_DATA.0 | Self::SMBUS_BLOCK_DATA.0,
+    );
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Functionality(bits)
+    }
+
+    pub const fn contains(self, other: Functionality) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Functionality {
+    type Output = Functionality;
+
+    fn bitor(self, other: Functionality) -> Functionality {
+        Functionality(self.0 | other.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmbusOp {
+    Quick { read: bool },
+    ReadByte,
+    WriteByte(u8),
+    ReadByteData(u8),
+    WriteByteData(u8, u8),
+    ReadWordData(u8),
+    WriteWordData(u8, u16),
+    ReadBlockData(u8),
+    WriteBlockData(u8, Vec<u8>),
+}
+
+impl SmbusOp {
+    /// Functionality needed to run the operation
+    pub fn requires(&self) -> Functionality {
+        match self {
+            SmbusOp::Quick { .. } => Functionality::SMBUS_QUICK,
+            SmbusOp::ReadByte | SmbusOp::WriteByte(_) => Functionality::SMBUS_BYTE,
+            SmbusOp::ReadByteData(_) | SmbusOp::WriteByteData(..) => Functionality::SMBUS_BYTE_DATA,
+            SmbusOp::ReadWordData(_) | SmbusOp::WriteWordData(..) => Functionality::SMBUS_WORD_DATA,
+            SmbusOp::ReadBlockData(_) | SmbusOp::WriteBlockData(..) => Functionality::SMBUS_BLOCK_DATA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmbusData {
+    None,
+    Byte(u8),
+    Word(u16),
+    Block(Vec<u8>),
+}
+
+pub trait I2cAdapter {
+    fn functionality(&self) -> Functionality;
+
+    /// Run `messages` as one transaction, filling in the read ones. Only
+    /// called with valid addresses and the functionality advertised
+    fn transfer(&mut self, _messages: &mut [I2cMessage]) -> Result<(), I2cError> {
+        Err(I2cError::NotSupported)
+    }
+
+    /// Run an SMBus operation on the device at the 7-bit `address`;
+    /// adapters doing SMBus in hardware replace the emulation
+    fn smbus(&mut self, address: u16, op: &SmbusOp) -> Result<SmbusData, I2cError> {
+        emulate_smbus(self, address, op)
+    }
+}
+
+/// An SMBus operation as I2C messages
+pub fn emulate_smbus<A: I2cAdapter + ?Sized>(
+    adapter: &mut A,
+    address: u16,
+    op: &SmbusOp,
+) -> Result<SmbusData, I2cError> {
+    let write = |data: &[u8]| I2cMessage::write(address, data);
+    let read = |length| I2cMessage::read(address, length);
+    let mut messages = match op {
+        SmbusOp::Quick { read: true } => vec![read(0)],
+        SmbusOp::Quick { read: false } => vec![write(&[])],
+        SmbusOp::ReadByte => vec![read(1)],
+        SmbusOp::WriteByte(value) => vec![write(&[*value])],
+        SmbusOp::ReadByteData(command) => vec![write(&[*command]), read(1)],
+        SmbusOp::WriteByteData(command, value) => vec![write(&[*command, *value])],
+        SmbusOp::ReadWordData(command) => vec![write(&[*command]), read(2)],
+        SmbusOp::WriteWordData(command, value) => {
+            let [low, high] = value.to_le_bytes();
+            vec![write(&[*command, low, high])]
+        }
+        SmbusOp::ReadBlockData(command) => {
+            let mut block = read(1 + SMBUS_BLOCK_MAX);
+            block.flags |= I2C_M_RECV_LEN;
+            vec![write(&[*command]), block]
+        }
+        SmbusOp::WriteBlockData(command, data) => {
+            let mut bytes = vec![*command, data.len() as u8];
+            bytes.extend_from_slice(data);
+            vec![write(&bytes)]
+        }
+    };
+    adapter.transfer(&mut messages)?;
+
+    let reply = messages.last().map(|message| message.data.as_slice()).unwrap_or(&[]);
+    Ok(match op {
+        SmbusOp::ReadByte | SmbusOp::ReadByteData(_) => SmbusData::Byte(*reply.first().ok_or(I2cError::BusError)?),
+        SmbusOp::ReadWordData(_) => {
+            let word = reply.get(..2).ok_or(I2cError::BusError)?;
+            SmbusData::Word(u16::from_le_bytes([word[0], word[1]]))
+        }
+        SmbusOp::ReadBlockData(_) => {
+            let count = *reply.first().ok_or(I2cError::BusError)? as usize;
+            if count > SMBUS_BLOCK_MAX || reply.len() < 1 + count {
+                return Err(I2cError::BusError);
+            }
+            SmbusData::Block(reply[1..1 + count].to_vec())
+        }
+        _ => SmbusData::None,
+    })
+}
+
+/// Check and run a transfer
+pub fn i2c_transfer(adapter: &mut dyn I2cAdapter, messages: &mut [I2cMessage]) -> Result<(), I2cError> {
+    let functionality = adapter.functionality();
+    if messages.is_empty() || !functionality.contains(Functionality::I2C) {
+        return Err(if messages.is_empty() { I2cError::InvalidArgument } else { I2cError::NotSupported });
+    }
+    for message in messages.iter() {
+        if !message.address_valid() {
+            return Err(I2cError::InvalidArgument);
+        }
+        if message.flags & I2C_M_TEN != 0 && !functionality.contains(Functionality::TEN_BIT) {
+            return Err(I2cError::NotSupported);
+        }
+        if message.flags & I2C_M_RECV_LEN != 0
+            && (!functionality.contains(Functionality::RECV_LEN) || message.data.len() < 1 + SMBUS_BLOCK_MAX)
+        {
+            return Err(I2cError::NotSupported);
+        }
+    }
+    adapter.transfer(messages)
+}
+
+/// Check and run an SMBus operation
+pub fn smbus_transfer(adapter: &mut dyn I2cAdapter, address: u16, op: &SmbusOp) -> Result<SmbusData, I2cError> {
+    if !(I2C_ADDRESS_MIN..=I2C_ADDRESS_MAX).contains(&address) {
+        return Err(I2cError::InvalidArgument);
+    }
+    if let SmbusOp::WriteBlockData(_, data) = op {
+        if data.is_empty() || data.len() > SMBUS_BLOCK_MAX {
+            return Err(I2cError::InvalidArgument);
+        }
+    }
+    if !adapter.functionality().contains(op.requires()) {
+        return Err(I2cError::NotSupported);
+    }
+    adapter.smbus(address, op)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An EEPROM-like device at 0x50: a write sets the register pointer
+    /// and stores what follows, a read returns from the pointer on;
+    /// register 0x80 answers SMBus blocks
+    pub(crate) struct Eeprom {
+        pub memory: [u8; 256],
+        pub pointer: u8,
+        pub transfers: usize,
+    }
+
+    impl Eeprom {
+        pub fn new() -> Self {
+            let mut memory = [0; 256];
+            memory[0x80..0x84].copy_from_slice(&[3, 0xAA, 0xBB, 0xCC]);
+            Self { memory, pointer: 0, transfers: 0 }
+        }
+    }
+
+    impl I2cAdapter for Eeprom {
+        fn functionality(&self) -> Functionality {
+            Functionality::I2C | Functionality::RECV_LEN | Functionality::SMBUS_EMULATED
+        }
+
+        fn transfer(&mut self, messages: &mut [I2cMessage]) -> Result<(), I2cError> {
+            self.transfers += 1;
+            for message in messages.iter_mut() {
+                if message.address != 0x50 {
+                    return Err(I2cError::NoAck);
+                }
+                if message.is_read() {
+                    for byte in message.data.iter_mut() {
+                        *byte = self.memory[self.pointer as usize];
+                        self.pointer = self.pointer.wrapping_add(1);
+                    }
+                    if message.flags & I2C_M_RECV_LEN != 0 {
+                        let count = message.data[0] as usize;
+                        message.data.truncate(1 + count);
+                    }
+                } else if let Some((&pointer, data)) = message.data.split_first() {
+                    self.pointer = pointer;
+                    for &byte in data {
+                        self.memory[self.pointer as usize] = byte;
+                        self.pointer = self.pointer.wrapping_add(1);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emulates_smbus_with_transfers() {
+        let mut eeprom = Eeprom::new();
+        assert_eq!(smbus_transfer(&mut eeprom, 0x50, &SmbusOp::WriteWordData(0x10, 0xBEEF)), Ok(SmbusData::None));
+        assert_eq!(smbus_transfer(&mut eeprom, 0x50, &SmbusOp::ReadByteData(0x10)), Ok(SmbusData::Byte(0xEF)));
+        assert_eq!(smbus_transfer(&mut eeprom, 0x50, &SmbusOp::ReadWordData(0x10)), Ok(SmbusData::Word(0xBEEF)));
+        // The pointer was left after the word
+        assert_eq!(smbus_transfer(&mut eeprom, 0x50, &SmbusOp::ReadByte), Ok(SmbusData::Byte(0)));
+        assert_eq!(
+            smbus_transfer(&mut eeprom, 0x50, &SmbusOp::ReadBlockData(0x80)),
+            Ok(SmbusData::Block(vec![0xAA, 0xBB, 0xCC]))
+        );
+        assert_eq!(smbus_transfer(&mut eeprom, 0x51, &SmbusOp::ReadByte), Err(I2cError::NoAck));
+
+        // Checked before the adapter sees them
+        let transfers = eeprom.transfers;
+        assert_eq!(smbus_transfer(&mut eeprom, 0x50, &SmbusOp::Quick { read: true }), Err(I2cError::NotSupported));
+        assert_eq!(smbus_transfer(&mut eeprom, 0x03, &SmbusOp::ReadByte), Err(I2cError::InvalidArgument));
+        assert_eq!(
+            smbus_transfer(&mut eeprom, 0x50, &SmbusOp::WriteBlockData(0, vec![0; 33])),
+            Err(I2cError::InvalidArgument)
+        );
+        let mut ten_bit = [I2cMessage { address: 0x150, flags: I2C_M_TEN, data: vec![0] }];
+        assert_eq!(i2c_transfer(&mut eeprom, &mut ten_bit), Err(I2cError::NotSupported));
+        assert_eq!(eeprom.transfers, transfers);
+    }
+}
@@ -0,0 +1,204 @@
+/*
+ * Orion Operating System - I2C Device Binding
+ *
+ * I2C devices cannot be probed: the firmware says what sits on a bus.
+ * On device tree boards every child node of a controller is a device,
+ * its address in `reg` and its identity in `compatible`; on ACPI
+ * machines a device below the controller has a _HID, maybe _CIDs, and
+ * an I2cSerialBusV2 resource in _CRS giving the address. Both become a
+ * BoardDevice, and each device is bound to the driver matching its most
+ * specific identity: the first `compatible` entry, or the _HID before
+ * the _CIDs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::adapter::{I2C_ADDRESS_MAX, I2C_ADDRESS_MIN, I2C_TEN_BIT_MAX};
+
+/// Bit of a device tree `reg` marking a 10-bit address
+pub const FDT_I2C_TEN_BIT_ADDRESS: u32 = 1 << 31;
+
+// ACPI I2cSerialBusV2 resource descriptor (ACPI 6.4, 6.4.3.8.2)
+const ACPI_SERIAL_BUS_DESCRIPTOR: u8 = 0x8E;
+const ACPI_SERIAL_BUS_TYPE_I2C: u8 = 1;
+const ACPI_I2C_TEN_BIT_MODE: u16 = 1 << 0;
+const ACPI_I2C_DESCRIPTOR_MIN: usize = 18;
+
+/// An identity a driver handles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceId {
+    /// Device tree "vendor,model"
+    Compatible(&'static str),
+    /// ACPI hardware or compatible id
+    Acpi(&'static str),
+}
+
+/// A driver of I2C devices and the devices it handles
+#[derive(Debug, Clone, Copy)]
+pub struct I2cDriverInfo {
+    pub name: &'static str,
+    pub ids: &'static [DeviceId],
+}
+
+/// The I2C device drivers of the system and what they handle; bus
+/// drivers bind the devices they find against it
+pub const I2C_DEVICE_DRIVERS: &[I2cDriverInfo] = &[
+    I2cDriverInfo {
+        name: "at24",
+        ids: &[
+            DeviceId::Compatible("atmel,24c02"),
+            DeviceId::Compatible("atmel,24c32"),
+            DeviceId::Compatible("atmel,24c256"),
+            DeviceId::Acpi("INT3499"),
+        ],
+    },
+    I2cDriverInfo {
+        name: "lm75",
+        ids: &[DeviceId::Compatible("national,lm75"), DeviceId::Compatible("ti,tmp75"), DeviceId::Acpi("LM750000")],
+    },
+    I2cDriverInfo { name: "jc42", ids: &[DeviceId::Compatible("jedec,jc-42.4-temp")] },
+];
+
+/// A device the firmware describes on a bus
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoardDevice {
+    pub address: u16,
+    pub ten_bit: bool,
+    /// Most specific first
+    pub compatible: Vec<String>,
+    /// _HID then the _CIDs
+    pub acpi_ids: Vec<String>,
+    /// Interrupt line, when the device has one
+    pub irq: Option<u32>,
+}
+
+impl BoardDevice {
+    /// From a device tree node: its `compatible` property, NUL separated
+    /// strings, and `reg`, one big-endian cell
+    pub fn from_fdt(compatible: &[u8], reg: &[u8], irq: Option<u32>) -> Option<Self> {
+        let cell = u32::from_be_bytes(reg.get(..4)?.try_into().ok()?);
+        let ten_bit = cell & FDT_I2C_TEN_BIT_ADDRESS != 0;
+        let address = (cell & 0xFFFF) as u16;
+        let compatible = compatible
+            .split(|&byte| byte == 0)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| core::str::from_utf8(entry).ok().map(String::from))
+            .collect::<Option<Vec<_>>>()?;
+        let device = Self { address, ten_bit, compatible, acpi_ids: Vec::new(), irq };
+        device.address_valid().then_some(device)
+    }
+
+    /// From an ACPI device: its _HID, its _CIDs and the I2cSerialBusV2
+    /// descriptor of its _CRS
+    pub fn from_acpi(hid: &str, cids: &[&str], descriptor: &[u8], irq: Option<u32>) -> Option<Self> {
+        if descriptor.len() < ACPI_I2C_DESCRIPTOR_MIN
+            || descriptor[0] != ACPI_SERIAL_BUS_DESCRIPTOR
+            || descriptor[5] != ACPI_SERIAL_BUS_TYPE_I2C
+        {
+            return None;
+        }
+        let flags = u16::from_le_bytes([descriptor[7], descriptor[8]]);
+        let mut acpi_ids = Vec::with_capacity(1 + cids.len());
+        acpi_ids.push(String::from(hid));
+        acpi_ids.extend(cids.iter().map(|&cid| String::from(cid)));
+        let device = Self {
+            address: u16::from_le_bytes([descriptor[16], descriptor[17]]),
+            ten_bit: flags & ACPI_I2C_TEN_BIT_MODE != 0,
+            compatible: Vec::new(),
+            acpi_ids,
+            irq,
+        };
+        device.address_valid().then_some(device)
+    }
+
+    pub fn address_valid(&self) -> bool {
+        if self.ten_bit {
+            self.address <= I2C_TEN_BIT_MAX
+        } else {
+            (I2C_ADDRESS_MIN..=I2C_ADDRESS_MAX).contains(&self.address)
+        }
+    }
+
+    /// The identity shown for the device: its first compatible or _HID
+    pub fn identity(&self) -> &str {
+        self.compatible.first().or(self.acpi_ids.first()).map(String::as_str).unwrap_or("")
+    }
+
+    /// How well `driver` matches, lower is better, None for not at all
+    fn rank(&self, driver: &I2cDriverInfo) -> Option<usize> {
+        driver
+            .ids
+            .iter()
+            .filter_map(|id| match id {
+                DeviceId::Compatible(name) => self.compatible.iter().position(|entry| entry == name),
+                DeviceId::Acpi(name) => self.acpi_ids.iter().position(|entry| entry.eq_ignore_ascii_case(name)),
+            })
+            .min()
+    }
+}
+
+/// The driver of each device, an index into `drivers`; on a tie the
+/// first driver listed wins
+pub fn bind(devices: &[BoardDevice], drivers: &[I2cDriverInfo]) -> Vec<Option<usize>> {
+    devices
+        .iter()
+        .map(|device| {
+            drivers
+                .iter()
+                .enumerate()
+                .filter_map(|(index, driver)| Some((device.rank(driver)?, index)))
+                .min()
+                .map(|(_, index)| index)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const EEPROM: I2cDriverInfo =
+        I2cDriverInfo { name: "at24", ids: &[DeviceId::Compatible("atmel,24c02"), DeviceId::Acpi("INT3499")] };
+    const SENSOR: I2cDriverInfo =
+        I2cDriverInfo { name: "lm75", ids: &[DeviceId::Compatible("national,lm75"), DeviceId::Acpi("LM750000")] };
+    const GENERIC: I2cDriverInfo = I2cDriverInfo { name: "hwmon", ids: &[DeviceId::Compatible("national,lm75b")] };
+
+    /// An I2cSerialBusV2 descriptor for `address` at 400 kHz
+    fn descriptor(address: u16, flags: u16) -> Vec<u8> {
+        let mut bytes = vec![ACPI_SERIAL_BUS_DESCRIPTOR, 15, 0, 2, 0, ACPI_SERIAL_BUS_TYPE_I2C, 0x02];
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&[1, 6, 0]);
+        bytes.extend_from_slice(&400_000u32.to_le_bytes());
+        bytes.extend_from_slice(&address.to_le_bytes());
+        bytes.extend_from_slice(b"\\_SB.I2C0\0");
+        bytes
+    }
+
+    #[test]
+    fn test_binds_firmware_devices_to_drivers() {
+        let sensor = BoardDevice::from_fdt(b"national,lm75b\0national,lm75\0", &[0, 0, 0, 0x48], Some(33)).unwrap();
+        assert_eq!((sensor.address, sensor.irq, sensor.identity()), (0x48, Some(33), "national,lm75b"));
+        let eeprom = BoardDevice::from_acpi("INT3499", &["PNP0C50"], &descriptor(0x50, 0), None).unwrap();
+        assert_eq!((eeprom.address, eeprom.ten_bit, eeprom.identity()), (0x50, false, "INT3499"));
+        let unknown = BoardDevice::from_fdt(b"vendor,thing\0", &[0x80, 0, 0x01, 0x23], None).unwrap();
+        assert_eq!((unknown.address, unknown.ten_bit), (0x123, true));
+
+        // The most specific compatible wins over the order of the drivers
+        assert_eq!(bind(&[sensor.clone(), eeprom, unknown], &[SENSOR, EEPROM, GENERIC]), [Some(2), Some(1), None]);
+        assert_eq!(bind(&[sensor], &[SENSOR, EEPROM]), [Some(0)]);
+
+        // Reserved addresses and other serial buses are refused
+        assert_eq!(BoardDevice::from_fdt(b"vendor,thing\0", &[0, 0, 0, 0x7C], None), None);
+        let mut spi = descriptor(0x50, 0);
+        spi[5] = 2;
+        assert_eq!(BoardDevice::from_acpi("SPT0001", &[], &spi, None), None);
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Orion Operating System - I2C Bus
+ *
+ * A bus driver's adapter together with the devices the firmware put on
+ * it and the driver bound to each. Devices are known when the bus is
+ * created or added later, as the firmware enumeration reaches them.
+ * Transfers and SMBus operations go through the bus, which checks them
+ * before the adapter sees them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::adapter::{i2c_transfer, smbus_transfer, I2cAdapter, I2cError, I2cMessage, SmbusData, SmbusOp};
+use crate::binding::{bind, BoardDevice, I2cDriverInfo};
+
+pub struct I2cBus<A: I2cAdapter> {
+    adapter: A,
+    devices: Vec<BoardDevice>,
+    /// Name of the driver of each device
+    bound: Vec<Option<&'static str>>,
+    drivers: &'static [I2cDriverInfo],
+}
+
+impl<A: I2cAdapter> I2cBus<A> {
+    /// A bus with `devices`, bound to the drivers they match
+    pub fn new(adapter: A, devices: Vec<BoardDevice>, drivers: &'static [I2cDriverInfo]) -> Self {
+        let bound = bind(&devices, drivers).into_iter().map(|index| index.map(|index| drivers[index].name)).collect();
+        Self { adapter, devices, bound, drivers }
+    }
+
+    pub fn adapter(&self) -> &A {
+        &self.adapter
+    }
+
+    pub fn adapter_mut(&mut self) -> &mut A {
+        &mut self.adapter
+    }
+
+    /// The devices and their drivers
+    pub fn devices(&self) -> impl Iterator<Item = (&BoardDevice, Option<&'static str>)> {
+        self.devices.iter().zip(self.bound.iter().copied())
+    }
+
+    /// Add a device and bind it; its driver. Two devices cannot share an
+    /// address
+    pub fn add_device(&mut self, device: BoardDevice) -> Result<Option<&'static str>, I2cError> {
+        if !device.address_valid()
+            || self.devices.iter().any(|known| known.address == device.address && known.ten_bit == device.ten_bit)
+        {
+            return Err(I2cError::InvalidArgument);
+        }
+        let driver = bind(core::slice::from_ref(&device), self.drivers)[0].map(|index| self.drivers[index].name);
+        self.devices.push(device);
+        self.bound.push(driver);
+        Ok(driver)
+    }
+
+    /// The devices bound to `driver`
+    pub fn devices_of<'a>(&'a self, driver: &'a str) -> impl Iterator<Item = &'a BoardDevice> {
+        self.devices().filter(move |(_, name)| *name == Some(driver)).map(|(device, _)| device)
+    }
+
+    pub fn transfer(&mut self, messages: &mut [I2cMessage]) -> Result<(), I2cError> {
+        i2c_transfer(&mut self.adapter, messages)
+    }
+
+    pub fn smbus(&mut self, address: u16, op: &SmbusOp) -> Result<SmbusData, I2cError> {
+        smbus_transfer(&mut self.adapter, address, op)
+    }
+}
@@ -0,0 +1,218 @@
+/*
+ * Orion Operating System - I2C Client
+ *
+ * Client side of the I2C control requests, used by the drivers of the
+ * devices on a bus and by tools. The transport is a trait so callers
+ * can use the bus driver's IPC channel and tests call straight into
+ * handle_control.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::adapter::{Functionality, I2cMessage, SmbusData, SmbusOp};
+use crate::control::{
+    encode_messages, encode_smbus, put_blob, read_string, read_u16, read_u32, DEVICE_HAS_IRQ, DEVICE_SOURCE_ACPI,
+    DEVICE_SOURCE_FDT, DEVICE_TEN_BIT, I2C_CTRL_ADD_DEVICE, I2C_CTRL_DEVICES, I2C_CTRL_FUNCS, I2C_CTRL_SMBUS,
+    I2C_CTRL_TRANSFER, STATUS_EIO, STATUS_OK,
+};
+
+/// Carries a request to a bus driver and returns its reply, None when
+/// the driver did not answer
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// A device on the bus as DEVICES lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceEntry {
+    pub address: u16,
+    pub ten_bit: bool,
+    pub irq: Option<u32>,
+    pub identity: String,
+    /// Empty when no driver matched
+    pub driver: String,
+}
+
+pub struct I2cClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> I2cClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.transport.call(request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    pub fn functionality(&mut self) -> Result<Functionality, i32> {
+        let payload = self.call(&I2C_CTRL_FUNCS.to_le_bytes())?;
+        read_u32(&payload, 0).map(Functionality::from_bits).ok_or(STATUS_EIO)
+    }
+
+    pub fn devices(&mut self) -> Result<Vec<DeviceEntry>, i32> {
+        let payload = self.call(&I2C_CTRL_DEVICES.to_le_bytes())?;
+        let count = read_u32(&payload, 0).ok_or(STATUS_EIO)? as usize;
+        let mut offset = 4;
+        let mut devices = Vec::new();
+        for _ in 0..count {
+            let parse = || {
+                let address = read_u16(&payload, offset)?;
+                let flags = read_u16(&payload, offset + 2)?;
+                let irq = read_u32(&payload, offset + 4)?;
+                let (identity, next) = read_string(&payload, offset + 8)?;
+                let (driver, next) = read_string(&payload, next)?;
+                let irq = (flags & DEVICE_HAS_IRQ != 0).then_some(irq);
+                Some((DeviceEntry { address, ten_bit: flags & DEVICE_TEN_BIT != 0, irq, identity, driver }, next))
+            };
+            let (device, next) = parse().ok_or(STATUS_EIO)?;
+            devices.push(device);
+            offset = next;
+        }
+        Ok(devices)
+    }
+
+    /// Run `messages` as one transaction, filling in the reads
+    pub fn transfer(&mut self, messages: &mut [I2cMessage]) -> Result<(), i32> {
+        let mut request = I2C_CTRL_TRANSFER.to_le_bytes().to_vec();
+        encode_messages(messages, &mut request);
+        let payload = self.call(&request)?;
+        let mut offset = 0;
+        for message in messages.iter_mut().filter(|message| message.is_read()) {
+            let length = read_u32(&payload, offset).ok_or(STATUS_EIO)? as usize;
+            message.data = payload.get(offset + 4..offset + 4 + length).ok_or(STATUS_EIO)?.to_vec();
+            offset += 4 + length;
+        }
+        Ok(())
+    }
+
+    fn add_device(&mut self, source: u32, irq: Option<u32>, description: &[u8]) -> Result<String, i32> {
+        let mut request = I2C_CTRL_ADD_DEVICE.to_le_bytes().to_vec();
+        let flags = if irq.is_some() { DEVICE_HAS_IRQ as u32 } else { 0 };
+        for field in [source, flags, irq.unwrap_or(0)] {
+            request.extend_from_slice(&field.to_le_bytes());
+        }
+        request.extend_from_slice(description);
+        let payload = self.call(&request)?;
+        read_string(&payload, 0).map(|(driver, _)| driver).ok_or(STATUS_EIO)
+    }
+
+    /// Add the device of a device tree node; the driver bound to it, ""
+    /// when none
+    pub fn add_fdt_device(&mut self, compatible: &[u8], reg: &[u8], irq: Option<u32>) -> Result<String, i32> {
+        let mut description = Vec::new();
+        put_blob(&mut description, compatible);
+        put_blob(&mut description, reg);
+        self.add_device(DEVICE_SOURCE_FDT, irq, &description)
+    }
+
+    /// Add the device of an ACPI namespace node; the driver bound to it,
+    /// "" when none
+    pub fn add_acpi_device(
+        &mut self,
+        hid: &str,
+        cids: &[&str],
+        descriptor: &[u8],
+        irq: Option<u32>,
+    ) -> Result<String, i32> {
+        let mut description = Vec::new();
+        put_blob(&mut description, hid.as_bytes());
+        description.extend_from_slice(&(cids.len() as u32).to_le_bytes());
+        for cid in cids {
+            put_blob(&mut description, cid.as_bytes());
+        }
+        put_blob(&mut description, descriptor);
+        self.add_device(DEVICE_SOURCE_ACPI, irq, &description)
+    }
+
+    pub fn smbus(&mut self, address: u16, op: &SmbusOp) -> Result<SmbusData, i32> {
+        let mut request = I2C_CTRL_SMBUS.to_le_bytes().to_vec();
+        encode_smbus(address, op, &mut request);
+        let payload = self.call(&request)?;
+        let value = || read_u32(&payload, 0).ok_or(STATUS_EIO);
+        Ok(match op {
+            SmbusOp::ReadByte | SmbusOp::ReadByteData(_) => SmbusData::Byte(value()? as u8),
+            SmbusOp::ReadWordData(_) => SmbusData::Word(value()? as u16),
+            SmbusOp::ReadBlockData(_) => SmbusData::Block(payload),
+            _ => SmbusData::None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::tests::Eeprom;
+    use crate::adapter::I2cError;
+    use crate::binding::{BoardDevice, DeviceId, I2cDriverInfo};
+    use crate::bus::I2cBus;
+    use crate::control::{handle_control, STATUS_EINVAL, STATUS_EPERM};
+    use alloc::vec;
+
+    struct Direct<'a> {
+        bus: &'a mut I2cBus<Eeprom>,
+        admin: bool,
+    }
+
+    impl Transport for Direct<'_> {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            Some(handle_control(self.bus, request, self.admin))
+        }
+    }
+
+    const DRIVERS: &[I2cDriverInfo] = &[
+        I2cDriverInfo { name: "at24", ids: &[DeviceId::Compatible("atmel,24c02")] },
+        I2cDriverInfo { name: "lm75", ids: &[DeviceId::Acpi("LM750000")] },
+    ];
+
+    #[test]
+    fn test_reaches_the_bus_through_control_requests() {
+        let devices = vec![
+            BoardDevice::from_fdt(b"atmel,24c02\0", &[0, 0, 0, 0x50], None).unwrap(),
+            BoardDevice::from_fdt(b"vendor,unknown\0", &[0, 0, 0, 0x20], Some(7)).unwrap(),
+        ];
+        let mut bus = I2cBus::new(Eeprom::new(), devices, DRIVERS);
+        assert_eq!(bus.devices_of("at24").map(|device| device.address).collect::<Vec<_>>(), [0x50]);
+
+        let mut client = I2cClient::new(Direct { bus: &mut bus, admin: false });
+        assert!(client.functionality().unwrap().contains(Functionality::SMBUS_BLOCK_DATA));
+        let listed = client.devices().unwrap();
+        assert_eq!((listed[0].address, listed[0].driver.as_str()), (0x50, "at24"));
+        assert_eq!(
+            (listed[1].irq, listed[1].identity.as_str(), listed[1].driver.as_str()),
+            (Some(7), "vendor,unknown", "")
+        );
+        assert_eq!(client.smbus(0x50, &SmbusOp::ReadByte), Err(STATUS_EPERM));
+
+        let mut client = I2cClient::new(Direct { bus: &mut bus, admin: true });
+        let mut messages = [I2cMessage::write(0x50, &[0x80]), I2cMessage::read(0x50, 4)];
+        client.transfer(&mut messages).unwrap();
+        assert_eq!(messages[1].data, [3, 0xAA, 0xBB, 0xCC]);
+        client.smbus(0x50, &SmbusOp::WriteBlockData(0x40, vec![1, 2])).unwrap();
+        assert_eq!(client.smbus(0x50, &SmbusOp::ReadBlockData(0x80)), Ok(SmbusData::Block(vec![0xAA, 0xBB, 0xCC])));
+        assert_eq!(client.smbus(0x50, &SmbusOp::ReadWordData(0x41)), Ok(SmbusData::Word(0x0201)));
+        assert_eq!(client.smbus(0x51, &SmbusOp::ReadByte), Err(I2cError::NoAck.status()));
+
+        // Devices the firmware enumeration finds later
+        let mut descriptor = vec![0x8E, 15, 0, 2, 0, 1, 0x02, 0, 0, 1, 6, 0];
+        descriptor.extend_from_slice(&100_000u32.to_le_bytes());
+        descriptor.extend_from_slice(&0x48u16.to_le_bytes());
+        assert_eq!(client.add_acpi_device("LM750000", &[], &descriptor, Some(40)).as_deref(), Ok("lm75"));
+        assert_eq!(client.add_fdt_device(b"atmel,24c02\0", &[0, 0, 0, 0x50], None), Err(STATUS_EINVAL));
+        assert_eq!(client.add_fdt_device(b"atmel,24c02\0", &[0, 0, 0, 0x57], None).as_deref(), Ok("at24"));
+        assert_eq!(client.devices().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,296 @@
+/*
+ * Orion Operating System - I2C Control
+ *
+ * Requests an I2C bus driver accepts on its endpoint, from the drivers
+ * of the devices on the bus and from tools. All fields are little-endian;
+ * every request starts with a 32-bit opcode and every reply with a
+ * 32-bit signed status (0 or a negative errno). Strings are a `len: u32`
+ * followed by UTF-8 bytes.
+ *
+ *   FUNCS                         -> functionality:u32
+ *   DEVICES                       -> count:u32 {address:u16 flags:u16
+ *                                    irq:u32 identity driver}*
+ *   TRANSFER count:u32 {address:u16 flags:u16 length:u32 data}*
+ *                                 -> {length:u32 data}*
+ *   SMBUS    address:u32 op:u32 command:u32 value:u32 data
+ *                                 -> value:u32, or the block read
+ *   ADD_DEVICE source:u32 flags:u32 irq:u32 description
+ *                                 -> driver
+ *
+ * DEVICES lists what the firmware describes on the bus and the driver
+ * bound to each, "" when none; a device driver finds its devices there.
+ * TRANSFER runs its messages as one transaction, `data` present for the
+ * writes only, and returns what the reads got, in order. SMBUS runs one
+ * SmbusOp (SMBUS_* below): `command` and `value` where it has them, and
+ * `data` for a block write. Both need CAP_ADMIN, since they reach every
+ * device on the bus.
+ *
+ * ADD_DEVICE tells the bus driver of a device the firmware describes,
+ * as the firmware has it: for DEVICE_SOURCE_FDT the description is the
+ * node's `compatible` and `reg` properties, each a `len: u32` and the raw
+ * bytes, and for DEVICE_SOURCE_ACPI the _HID, `count: u32` _CIDs and the
+ * I2cSerialBusV2 descriptor from _CRS. `irq` counts when `flags` has
+ * DEVICE_HAS_IRQ. It returns the driver bound, "" when none, and needs
+ * CAP_ADMIN.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_proto::reply;
+
+use crate::adapter::{I2cAdapter, I2cMessage, SmbusData, SmbusOp};
+use crate::binding::BoardDevice;
+use crate::bus::I2cBus;
+
+// Opcodes
+pub const I2C_CTRL_FUNCS: u32 = 0x7001;
+pub const I2C_CTRL_DEVICES: u32 = 0x7002;
+pub const I2C_CTRL_TRANSFER: u32 = 0x7003;
+pub const I2C_CTRL_SMBUS: u32 = 0x7004;
+pub const I2C_CTRL_ADD_DEVICE: u32 = 0x7005;
+
+// ADD_DEVICE sources
+pub const DEVICE_SOURCE_FDT: u32 = 1;
+pub const DEVICE_SOURCE_ACPI: u32 = 2;
+
+// SMBUS operations
+pub const SMBUS_QUICK_WRITE: u32 = 0;
+pub const SMBUS_QUICK_READ: u32 = 1;
+pub const SMBUS_READ_BYTE: u32 = 2;
+pub const SMBUS_WRITE_BYTE: u32 = 3;
+pub const SMBUS_READ_BYTE_DATA: u32 = 4;
+pub const SMBUS_WRITE_BYTE_DATA: u32 = 5;
+pub const SMBUS_READ_WORD_DATA: u32 = 6;
+pub const SMBUS_WRITE_WORD_DATA: u32 = 7;
+pub const SMBUS_READ_BLOCK_DATA: u32 = 8;
+pub const SMBUS_WRITE_BLOCK_DATA: u32 = 9;
+
+// DEVICES flags
+pub const DEVICE_TEN_BIT: u16 = 1 << 0;
+pub const DEVICE_HAS_IRQ: u16 = 1 << 1;
+
+/// Most messages in one TRANSFER, and most bytes in one message
+pub const MAX_MESSAGES: usize = 16;
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EINVAL: i32 = -22;
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn read_blob(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let len = read_u32(data, offset)? as usize;
+    Some((data.get(offset + 4..offset + 4 + len)?, offset + 4 + len))
+}
+
+pub(crate) fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let (bytes, next) = read_blob(data, offset)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), next))
+}
+
+pub(crate) fn put_blob(out: &mut Vec<u8>, value: &[u8]) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    put_blob(out, value.as_bytes());
+}
+
+pub fn encode_messages(messages: &[I2cMessage], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(messages.len() as u32).to_le_bytes());
+    for message in messages {
+        out.extend_from_slice(&message.address.to_le_bytes());
+        out.extend_from_slice(&message.flags.to_le_bytes());
+        out.extend_from_slice(&(message.data.len() as u32).to_le_bytes());
+        if !message.is_read() {
+            out.extend_from_slice(&message.data);
+        }
+    }
+}
+
+pub fn decode_messages(data: &[u8]) -> Option<Vec<I2cMessage>> {
+    let count = read_u32(data, 0)? as usize;
+    if count > MAX_MESSAGES {
+        return None;
+    }
+    let mut offset = 4;
+    let mut messages = Vec::with_capacity(count);
+    for _ in 0..count {
+        let address = read_u16(data, offset)?;
+        let flags = read_u16(data, offset + 2)?;
+        let length = read_u32(data, offset + 4)? as usize;
+        if length > MAX_MESSAGE_LENGTH {
+            return None;
+        }
+        offset += 8;
+        let message = if flags & crate::adapter::I2C_M_RD != 0 {
+            I2cMessage { address, flags, data: vec![0; length] }
+        } else {
+            offset += length;
+            I2cMessage { address, flags, data: data.get(offset - length..offset)?.to_vec() }
+        };
+        messages.push(message);
+    }
+    Some(messages)
+}
+
+pub fn encode_smbus(address: u16, op: &SmbusOp, out: &mut Vec<u8>) {
+    let (kind, command, value, block): (u32, u8, u32, &[u8]) = match op {
+        SmbusOp::Quick { read: false } => (SMBUS_QUICK_WRITE, 0, 0, &[]),
+        SmbusOp::Quick { read: true } => (SMBUS_QUICK_READ, 0, 0, &[]),
+        SmbusOp::ReadByte => (SMBUS_READ_BYTE, 0, 0, &[]),
+        SmbusOp::WriteByte(value) => (SMBUS_WRITE_BYTE, 0, *value as u32, &[]),
+        SmbusOp::ReadByteData(command) => (SMBUS_READ_BYTE_DATA, *command, 0, &[]),
+        SmbusOp::WriteByteData(command, value) => (SMBUS_WRITE_BYTE_DATA, *command, *value as u32, &[]),
+        SmbusOp::ReadWordData(command) => (SMBUS_READ_WORD_DATA, *command, 0, &[]),
+        SmbusOp::WriteWordData(command, value) => (SMBUS_WRITE_WORD_DATA, *command, *value as u32, &[]),
+        SmbusOp::ReadBlockData(command) => (SMBUS_READ_BLOCK_DATA, *command, 0, &[]),
+        SmbusOp::WriteBlockData(command, data) => (SMBUS_WRITE_BLOCK_DATA, *command, 0, data),
+    };
+    for field in [address as u32, kind, command as u32, value] {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out.extend_from_slice(block);
+}
+
+pub fn decode_smbus(data: &[u8]) -> Option<(u16, SmbusOp)> {
+    let address = u16::try_from(read_u32(data, 0)?).ok()?;
+    let command = u8::try_from(read_u32(data, 8)?).ok()?;
+    let value = read_u32(data, 12)?;
+    let byte = || u8::try_from(value).ok();
+    let op = match read_u32(data, 4)? {
+        SMBUS_QUICK_WRITE => SmbusOp::Quick { read: false },
+        SMBUS_QUICK_READ => SmbusOp::Quick { read: true },
+        SMBUS_READ_BYTE => SmbusOp::ReadByte,
+        SMBUS_WRITE_BYTE => SmbusOp::WriteByte(byte()?),
+        SMBUS_READ_BYTE_DATA => SmbusOp::ReadByteData(command),
+        SMBUS_WRITE_BYTE_DATA => SmbusOp::WriteByteData(command, byte()?),
+        SMBUS_READ_WORD_DATA => SmbusOp::ReadWordData(command),
+        SMBUS_WRITE_WORD_DATA => SmbusOp::WriteWordData(command, u16::try_from(value).ok()?),
+        SMBUS_READ_BLOCK_DATA => SmbusOp::ReadBlockData(command),
+        SMBUS_WRITE_BLOCK_DATA => SmbusOp::WriteBlockData(command, data.get(16..)?.to_vec()),
+        _ => return None,
+    };
+    Some((address, op))
+}
+
+/// The device an ADD_DEVICE argument describes
+pub fn decode_device(data: &[u8]) -> Option<BoardDevice> {
+    let flags = read_u32(data, 4)? as u16;
+    let irq = read_u32(data, 8)?;
+    let irq = (flags & DEVICE_HAS_IRQ != 0).then_some(irq);
+    match read_u32(data, 0)? {
+        DEVICE_SOURCE_FDT => {
+            let (compatible, next) = read_blob(data, 12)?;
+            let (reg, _) = read_blob(data, next)?;
+            BoardDevice::from_fdt(compatible, reg, irq)
+        }
+        DEVICE_SOURCE_ACPI => {
+            let (hid, mut offset) = read_string(data, 12)?;
+            let count = read_u32(data, offset)? as usize;
+            offset += 4;
+            let mut cids = Vec::with_capacity(count.min(8));
+            for _ in 0..count {
+                let (cid, next) = read_string(data, offset)?;
+                cids.push(cid);
+                offset = next;
+            }
+            let cids: Vec<&str> = cids.iter().map(String::as_str).collect();
+            let (descriptor, _) = read_blob(data, offset)?;
+            BoardDevice::from_acpi(&hid, &cids, descriptor, irq)
+        }
+        _ => None,
+    }
+}
+
+/// Serve a control request. `admin` is whether the sender holds
+/// CAP_ADMIN, which the driver checks
+pub fn handle_control<A: I2cAdapter>(bus: &mut I2cBus<A>, request: &[u8], admin: bool) -> Vec<u8> {
+    let Some(opcode) = read_u32(request, 0) else {
+        return reply(STATUS_EINVAL, &[]);
+    };
+    let argument = &request[4..];
+    match opcode {
+        I2C_CTRL_FUNCS => reply(STATUS_OK, &bus.adapter().functionality().bits().to_le_bytes()),
+        I2C_CTRL_DEVICES => {
+            let mut payload = Vec::new();
+            payload.extend_from_slice(&(bus.devices().count() as u32).to_le_bytes());
+            for (device, driver) in bus.devices() {
+                let mut flags = 0;
+                if device.ten_bit {
+                    flags |= DEVICE_TEN_BIT;
+                }
+                if device.irq.is_some() {
+                    flags |= DEVICE_HAS_IRQ;
+                }
+                payload.extend_from_slice(&device.address.to_le_bytes());
+                payload.extend_from_slice(&flags.to_le_bytes());
+                payload.extend_from_slice(&device.irq.unwrap_or(0).to_le_bytes());
+                put_string(&mut payload, device.identity());
+                put_string(&mut payload, driver.unwrap_or(""));
+            }
+            reply(STATUS_OK, &payload)
+        }
+        I2C_CTRL_TRANSFER | I2C_CTRL_SMBUS | I2C_CTRL_ADD_DEVICE if !admin => reply(STATUS_EPERM, &[]),
+        I2C_CTRL_TRANSFER => {
+            let Some(mut messages) = decode_messages(argument) else {
+                return reply(STATUS_EINVAL, &[]);
+            };
+            if let Err(error) = bus.transfer(&mut messages) {
+                return reply(error.status(), &[]);
+            }
+            let mut payload = Vec::new();
+            for message in messages.iter().filter(|message| message.is_read()) {
+                payload.extend_from_slice(&(message.data.len() as u32).to_le_bytes());
+                payload.extend_from_slice(&message.data);
+            }
+            reply(STATUS_OK, &payload)
+        }
+        I2C_CTRL_SMBUS => {
+            let Some((address, op)) = decode_smbus(argument) else {
+                return reply(STATUS_EINVAL, &[]);
+            };
+            match bus.smbus(address, &op) {
+                Ok(SmbusData::None) => reply(STATUS_OK, &[]),
+                Ok(SmbusData::Byte(value)) => reply(STATUS_OK, &(value as u32).to_le_bytes()),
+                Ok(SmbusData::Word(value)) => reply(STATUS_OK, &(value as u32).to_le_bytes()),
+                Ok(SmbusData::Block(data)) => reply(STATUS_OK, &data),
+                Err(error) => reply(error.status(), &[]),
+            }
+        }
+        I2C_CTRL_ADD_DEVICE => {
+            let Some(device) = decode_device(argument) else {
+                return reply(STATUS_EINVAL, &[]);
+            };
+            match bus.add_device(device) {
+                Ok(driver) => {
+                    let mut payload = Vec::new();
+                    put_string(&mut payload, driver.unwrap_or(""));
+                    reply(STATUS_OK, &payload)
+                }
+                Err(error) => reply(error.status(), &[]),
+            }
+        }
+        _ => reply(STATUS_EINVAL, &[]),
+    }
+}
@@ -0,0 +1,37 @@
+/*
+ * Orion Operating System - I2C and SMBus
+ *
+ * Core of the I2C bus drivers, for sensors, EEPROMs and the other small
+ * devices of boards and PC chipsets. A bus driver implements I2cAdapter
+ * for its controller (see adapter.rs), builds an I2cBus from it and
+ * serves the control requests on its endpoint (see control.rs). The
+ * devices the device tree or ACPI describe below the controller are
+ * added to the bus, each bound to the driver that handles it (see
+ * binding.rs). Device drivers and tools reach their devices through
+ * I2cClient.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod adapter;
+pub mod binding;
+pub mod bus;
+pub mod client;
+pub mod control;
+
+pub use adapter::{
+    emulate_smbus, i2c_transfer, smbus_transfer, Functionality, I2cAdapter, I2cError, I2cMessage, SmbusData, SmbusOp,
+    I2C_M_RD, I2C_M_RECV_LEN, I2C_M_TEN, SMBUS_BLOCK_MAX,
+};
+pub use binding::{bind, BoardDevice, DeviceId, I2cDriverInfo, I2C_DEVICE_DRIVERS};
+pub use bus::I2cBus;
+pub use client::{DeviceEntry, I2cClient, Transport};
+pub use control::{handle_control, DEVICE_SOURCE_ACPI, DEVICE_SOURCE_FDT};
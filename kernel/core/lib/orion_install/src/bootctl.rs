@@ -0,0 +1,304 @@
+/*
+ * Orion Operating System - Boot Control Record
+ *
+ * What the bootloader needs to start an installed system, kept in a small
+ * partition of its own (gpt::BOOT_CONTROL_TYPE) rather than on the FAT
+ * system partition so that it can be rewritten in place: the two system
+ * slots with their partition GUID, state and kernel path, the slot to
+ * boot, the data partition and the kernel command line. The bootloader
+ * passes `root=PARTUUID=<slot guid>` and `data=PARTUUID=<data guid>` in
+ * front of the command line. The layout is struct orion_boot_control of
+ * orion-boot-protocol.h:
+ *
+ *   0    magic:u32 version:u32 sequence:u32 active_slot:u32
+ *   16   slot[2] { guid[16] flags:u32 tries:u32 kernel[64] reserved[8] }
+ *   208  data_guid[16]
+ *   224  cmdline[256]
+ *   480  reserved[28] checksum:u32
+ *
+ * The record is kept twice, in the first two 4 KiB of the partition, and
+ * each write goes to the copy not holding the newest one, so a write torn
+ * by a power cut leaves the previous record in force. The checksum is
+ * orion_checksum over the first 508 bytes.
+ *
+ * A slot that has not completed a boot yet (an update just written to
+ * it) is tried `tries` times: the bootloader takes one try per boot, and
+ * once none are left the slot is marked unbootable and the other one is
+ * started instead (select, orion_boot_control_select in C). The update
+ * server marks the slot successful when init finished the boot in time.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::gpt::{Guid, Partition};
+use crate::{read_at, write_at, Disk, InstallError};
+
+pub const RECORD_SIZE: usize = 512;
+pub const MAGIC: u32 = 0x5443_424F; // "OBCT"
+pub const VERSION: u32 = 1;
+/// Distance between the two copies
+const COPY_STRIDE: u64 = 4096;
+const KERNEL_SIZE: usize = 64;
+const CMDLINE_SIZE: usize = 256;
+const CHECKSUM_OFFSET: usize = RECORD_SIZE - 4;
+
+/// Kernel inside the system partition unless a slot says otherwise
+pub const DEFAULT_KERNEL: &str = "/boot/orion-kernel.elf";
+
+// Slot flags
+/// The slot holds a complete system image
+pub const SLOT_BOOTABLE: u32 = 1 << 0;
+/// A boot from the slot completed
+pub const SLOT_SUCCESSFUL: u32 = 1 << 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootSlot {
+    pub guid: Guid,
+    pub flags: u32,
+    /// Boots left before the bootloader gives up on an unsuccessful slot
+    pub tries: u32,
+    pub kernel: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootControl {
+    pub sequence: u32,
+    pub active_slot: u32,
+    pub slots: [BootSlot; 2],
+    pub data_guid: Guid,
+    pub cmdline: String,
+}
+
+/// orion_checksum of orion-boot-protocol.h
+pub fn orion_checksum(data: &[u8]) -> u32 {
+    !data.iter().fold(0u32, |checksum, byte| checksum.wrapping_add(*byte as u32).rotate_left(1))
+}
+
+fn put_text(out: &mut [u8], text: &str) {
+    // Always leave a terminating NUL
+    let length = text.len().min(out.len() - 1);
+    out[..length].copy_from_slice(&text.as_bytes()[..length]);
+}
+
+fn text(data: &[u8]) -> String {
+    let end = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+impl BootSlot {
+    pub fn new(partition: &Partition) -> Self {
+        Self { guid: partition.guid, flags: 0, tries: 0, kernel: String::from(DEFAULT_KERNEL) }
+    }
+}
+
+impl BootControl {
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0u8; RECORD_SIZE];
+        for (index, value) in [MAGIC, VERSION, self.sequence, self.active_slot].iter().enumerate() {
+            record[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        for (index, slot) in self.slots.iter().enumerate() {
+            let entry = &mut record[16 + index * 96..16 + (index + 1) * 96];
+            entry[0..16].copy_from_slice(&slot.guid.0);
+            entry[16..20].copy_from_slice(&slot.flags.to_le_bytes());
+            entry[20..24].copy_from_slice(&slot.tries.to_le_bytes());
+            put_text(&mut entry[24..24 + KERNEL_SIZE], &slot.kernel);
+        }
+        record[208..224].copy_from_slice(&self.data_guid.0);
+        put_text(&mut record[224..224 + CMDLINE_SIZE], &self.cmdline);
+        let checksum = orion_checksum(&record[..CHECKSUM_OFFSET]);
+        record[CHECKSUM_OFFSET..].copy_from_slice(&checksum.to_le_bytes());
+        record
+    }
+
+    pub fn decode(record: &[u8]) -> Option<Self> {
+        let record = record.get(..RECORD_SIZE)?;
+        if read_u32(record, 0) != MAGIC
+            || read_u32(record, 4) != VERSION
+            || read_u32(record, CHECKSUM_OFFSET) != orion_checksum(&record[..CHECKSUM_OFFSET])
+        {
+            return None;
+        }
+        let slot = |index: usize| {
+            let entry = &record[16 + index * 96..16 + (index + 1) * 96];
+            BootSlot {
+                guid: Guid(entry[0..16].try_into().unwrap()),
+                flags: read_u32(entry, 16),
+                tries: read_u32(entry, 20),
+                kernel: text(&entry[24..24 + KERNEL_SIZE]),
+            }
+        };
+        Some(Self {
+            sequence: read_u32(record, 8),
+            active_slot: read_u32(record, 12).min(1),
+            slots: [slot(0), slot(1)],
+            data_guid: Guid(record[208..224].try_into().unwrap()),
+            cmdline: text(&record[224..224 + CMDLINE_SIZE]),
+        })
+    }
+
+    /// Slot the bootloader starts, taking a try from a slot not yet known
+    /// to boot and falling back to the other slot once it has none left.
+    /// None when neither slot can be booted. The record is stored again
+    /// when this changed it
+    pub fn select(&mut self) -> Option<usize> {
+        let mut slot = self.active_slot as usize;
+        for _ in 0..2 {
+            let entry = &mut self.slots[slot];
+            if entry.flags & SLOT_BOOTABLE != 0 {
+                if entry.flags & SLOT_SUCCESSFUL != 0 {
+                    return Some(slot);
+                }
+                if entry.tries > 0 {
+                    entry.tries -= 1;
+                    return Some(slot);
+                }
+                entry.flags &= !SLOT_BOOTABLE;
+            }
+            slot = 1 - slot;
+            self.active_slot = slot as u32;
+        }
+        None
+    }
+
+    /// The boot from `slot` completed
+    pub fn mark_successful(&mut self, slot: usize) {
+        self.slots[slot].flags |= SLOT_SUCCESSFUL;
+        self.slots[slot].tries = 0;
+    }
+
+    /// Take `slot` out of use, going back to the other slot if it was the
+    /// active one and can still boot
+    pub fn mark_unbootable(&mut self, slot: usize) {
+        self.slots[slot].flags = 0;
+        self.slots[slot].tries = 0;
+        if self.active_slot as usize == slot && self.slots[1 - slot].flags & SLOT_BOOTABLE != 0 {
+            self.active_slot = (1 - slot) as u32;
+        }
+    }
+
+    /// Boot `slot` from now on, with `tries` boots to complete one
+    pub fn activate(&mut self, slot: usize, tries: u32) {
+        self.slots[slot].flags = SLOT_BOOTABLE;
+        self.slots[slot].tries = tries;
+        self.active_slot = slot as u32;
+    }
+
+    /// Newest intact copy in `partition`
+    pub fn read(disk: &mut dyn Disk, partition: &Partition) -> Result<Self, InstallError> {
+        let block_size = disk.block_size() as u64;
+        let start = partition.first_lba * block_size;
+        let mut newest: Option<Self> = None;
+        for copy in 0..2 {
+            let mut data = vec![0u8; RECORD_SIZE.max(block_size as usize)];
+            read_at(disk, start + copy * COPY_STRIDE, &mut data)?;
+            if let Some(record) = Self::decode(&data) {
+                if newest.as_ref().is_none_or(|current| record.sequence > current.sequence) {
+                    newest = Some(record);
+                }
+            }
+        }
+        newest.ok_or(InstallError::NotFound)
+    }
+
+    /// Store the record with the next sequence number, over the older copy
+    pub fn write(&mut self, disk: &mut dyn Disk, partition: &Partition) -> Result<(), InstallError> {
+        let block_size = disk.block_size() as u64;
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut data: Vec<u8> = vec![0u8; RECORD_SIZE.max(block_size as usize)];
+        data[..RECORD_SIZE].copy_from_slice(&self.encode());
+        let copy = (self.sequence % 2) as u64;
+        write_at(disk, partition.first_lba * block_size + copy * COPY_STRIDE, &data)?;
+        disk.flush().map_err(InstallError::Disk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpt::BOOT_CONTROL_TYPE;
+    use crate::tests::MemoryDisk;
+
+    fn record() -> BootControl {
+        let slot = |byte| BootSlot { guid: Guid::random([byte; 16]), flags: 0, tries: 0, kernel: String::from(DEFAULT_KERNEL) };
+        BootControl {
+            sequence: 0,
+            active_slot: 0,
+            slots: [slot(1), slot(2)],
+            data_guid: Guid::random([3; 16]),
+            cmdline: String::from("console=ttyS0"),
+        }
+    }
+
+    #[test]
+    fn test_checksum_matches_the_c_definition() {
+        // Worked by hand: 'a' = 0x61 rotated left once, then complemented
+        assert_eq!(orion_checksum(b"a"), !0xC2);
+        assert_eq!(orion_checksum(b""), !0);
+    }
+
+    #[test]
+    fn test_torn_writes_leave_the_previous_record() {
+        let mut disk = MemoryDisk::new(512, 1 << 20);
+        let partition = Partition {
+            type_guid: BOOT_CONTROL_TYPE,
+            guid: Guid::random([9; 16]),
+            first_lba: 64,
+            last_lba: 1023,
+            attributes: 0,
+            name: String::from("boot_control"),
+        };
+        let mut control = record();
+        control.slots[0].flags = SLOT_BOOTABLE | SLOT_SUCCESSFUL;
+        control.write(&mut disk, &partition).unwrap();
+        assert_eq!(BootControl::read(&mut disk, &partition).unwrap(), control);
+
+        control.active_slot = 1;
+        control.write(&mut disk, &partition).unwrap();
+        assert_eq!(BootControl::read(&mut disk, &partition).unwrap().active_slot, 1);
+
+        // Tear the newest copy: the first one comes back
+        disk.data[64 * 512 + 20] ^= 0xff;
+        let read = BootControl::read(&mut disk, &partition).unwrap();
+        assert_eq!((read.sequence, read.active_slot), (1, 0));
+        assert_eq!(read.cmdline, "console=ttyS0");
+    }
+
+    #[test]
+    fn test_updates_fall_back_once_out_of_tries() {
+        let mut control = record();
+        control.slots[0].flags = SLOT_BOOTABLE | SLOT_SUCCESSFUL;
+        assert_eq!(control.select(), Some(0));
+
+        control.activate(1, 2);
+        assert_eq!(control.select(), Some(1));
+        assert_eq!(control.select(), Some(1));
+        assert_eq!(control.slots[1].tries, 0);
+        // Neither boot completed: the third one goes back to slot 0
+        assert_eq!(control.select(), Some(0));
+        assert_eq!((control.active_slot, control.slots[1].flags), (0, 0));
+
+        control.activate(1, 1);
+        assert_eq!(control.select(), Some(1));
+        control.mark_successful(1);
+        assert_eq!(control.select(), Some(1));
+        assert_eq!(control.slots[1].flags, SLOT_BOOTABLE | SLOT_SUCCESSFUL);
+
+        control.mark_unbootable(1);
+        assert_eq!(control.active_slot, 0);
+        control.mark_unbootable(0);
+        assert_eq!(control.select(), None);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * Orion Operating System - Initial Configuration State
+ *
+ * Files the configuration server finds on the first boot of an installed
+ * system, in the formats of services/config/src/store.rs: version 1 holds
+ * the configuration chosen at install time and is current and known good
+ * already, since the live system it was validated on just ran with it.
+ * Every file is a record of magic, sequence, body length and FNV-1a
+ * checksum followed by the body; state records carry the sequence 1,
+ * which the server writes to `state.1`.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// CONFIG_DIR of the configuration server, relative to the data file system
+pub const CONFIG_DIR: &str = "etc/orion/config";
+
+/// Configuration with nothing set: every server keeps its defaults
+pub const EMPTY_DOCUMENT: &str = r#"{"schema":1,"interfaces":[],"mounts":[],"access":[]}"#;
+
+const RECORD_MAGIC: &[u8; 4] = b"OCFG";
+/// Versions the server keeps, which picks the document file
+const MAX_HISTORY: u32 = 16;
+/// History origin of a version written by the installer
+pub const ORIGIN_INSTALL: u32 = 4;
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+fn encode_record(sequence: u64, body: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(20 + body.len());
+    record.extend_from_slice(RECORD_MAGIC);
+    record.extend_from_slice(&sequence.to_le_bytes());
+    record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    record.extend_from_slice(&fnv1a(body).to_le_bytes());
+    record.extend_from_slice(body);
+    record
+}
+
+/// Paths (relative to the data file system) and contents of the
+/// configuration server files; `time` is the realtime clock in nanoseconds
+pub fn initial_files(document: &[u8], time: u64) -> Vec<(String, Vec<u8>)> {
+    const VERSION: u32 = 1;
+    let comment = "installed";
+
+    let mut state = Vec::new();
+    // current, known_good, boot_attempts, next_version, history count
+    for value in [VERSION, VERSION, 0, VERSION + 1, 1] {
+        state.extend_from_slice(&value.to_le_bytes());
+    }
+    state.extend_from_slice(&VERSION.to_le_bytes());
+    state.extend_from_slice(&ORIGIN_INSTALL.to_le_bytes());
+    state.extend_from_slice(&time.to_le_bytes());
+    state.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+    state.extend_from_slice(comment.as_bytes());
+
+    alloc::vec![
+        (format!("{}/state.1", CONFIG_DIR), encode_record(1, &state)),
+        (format!("{}/version.{}", CONFIG_DIR, VERSION % MAX_HISTORY), encode_record(VERSION as u64, document)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_match_the_store_format() {
+        let files = initial_files(EMPTY_DOCUMENT.as_bytes(), 42);
+        assert_eq!(files[0].0, "etc/orion/config/state.1");
+        assert_eq!(files[1].0, "etc/orion/config/version.1");
+
+        let (_, document) = &files[1];
+        assert_eq!(&document[..4], b"OCFG");
+        assert_eq!(u64::from_le_bytes(document[4..12].try_into().unwrap()), 1);
+        assert_eq!(u32::from_le_bytes(document[12..16].try_into().unwrap()) as usize, EMPTY_DOCUMENT.len());
+        assert_eq!(u32::from_le_bytes(document[16..20].try_into().unwrap()), fnv1a(EMPTY_DOCUMENT.as_bytes()));
+        assert_eq!(&document[20..], EMPTY_DOCUMENT.as_bytes());
+
+        let (_, state) = &files[0];
+        let body = &state[20..];
+        let field = |index: usize| u32::from_le_bytes(body[index * 4..index * 4 + 4].try_into().unwrap());
+        assert_eq!([field(0), field(1), field(2), field(3), field(4)], [1, 1, 0, 2, 1]);
+        assert_eq!([field(5), field(6)], [1, ORIGIN_INSTALL]);
+        assert_eq!(&body[40..], b"installed");
+    }
+}
@@ -0,0 +1,562 @@
+/*
+ * Orion Operating System - ext2 Formatting
+ *
+ * Builds an ext2 revision 1 file system on a partition, with the files
+ * the installed system needs on its first boot already in it. The layout
+ * is the one mke2fs produces for 4 KiB blocks: block groups of up to
+ * 32768 blocks, superblock and descriptor table backups in groups 0, 1
+ * and the powers of 3, 5 and 7 (sparse_super), directory entries carrying
+ * the file type. Nothing newer is used, so any ext2, ext3 or ext4 driver
+ * mounts the result.
+ *
+ * The tree is small and known before anything is written, so inodes and
+ * blocks are handed out in order from the start of group 0 and every
+ * directory fits one block; files of up to 12 + 1024 blocks use direct
+ * and single indirect blocks.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{write_at, Disk, InstallError};
+
+pub const BLOCK_SIZE: usize = 4096;
+const LOG_BLOCK_SIZE: u32 = 2;
+const SUPERBLOCK_OFFSET: usize = 1024;
+const DESCRIPTOR_SIZE: usize = 32;
+const INODE_SIZE: usize = 128;
+const INODES_PER_BLOCK: u32 = (BLOCK_SIZE / INODE_SIZE) as u32;
+const MAX_INODES_PER_GROUP: u32 = 8192;
+const ROOT_INODE: u32 = 2;
+const FIRST_INODE: u32 = 11;
+const DIRECT_BLOCKS: usize = 12;
+const POINTERS_PER_BLOCK: usize = BLOCK_SIZE / 4;
+
+const MAGIC: u16 = 0xEF53;
+const STATE_CLEAN: u16 = 1;
+const ERRORS_CONTINUE: u16 = 1;
+const REVISION_DYNAMIC: u32 = 1;
+const FEATURE_INCOMPAT_FILETYPE: u32 = 0x0002;
+const FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+
+const MODE_DIRECTORY: u16 = 0x4000;
+const MODE_REGULAR: u16 = 0x8000;
+const FILE_TYPE_REGULAR: u8 = 1;
+const FILE_TYPE_DIRECTORY: u8 = 2;
+
+/// Smallest last group worth keeping beyond its own metadata
+const MIN_GROUP_DATA_BLOCKS: u64 = 64;
+
+enum Kind {
+    Directory,
+    File(Vec<u8>),
+}
+
+struct Node {
+    name: String,
+    parent: usize,
+    permissions: u16,
+    kind: Kind,
+}
+
+/// Group geometry shared by every group
+#[derive(Debug, Clone, Copy)]
+struct Geometry {
+    blocks: u64,
+    groups: u64,
+    blocks_per_group: u64,
+    inodes_per_group: u32,
+    descriptor_blocks: u64,
+}
+
+impl Geometry {
+    fn inode_table_blocks(&self) -> u64 {
+        (self.inodes_per_group / INODES_PER_BLOCK) as u64
+    }
+
+    /// Groups 0, 1 and powers of 3, 5 and 7 carry a superblock copy
+    fn has_superblock(group: u64) -> bool {
+        if group <= 1 {
+            return true;
+        }
+        [3, 5, 7].iter().any(|base| {
+            let mut power = *base;
+            while power < group {
+                power *= base;
+            }
+            power == group
+        })
+    }
+
+    fn group_start(&self, group: u64) -> u64 {
+        group * self.blocks_per_group
+    }
+
+    fn group_blocks(&self, group: u64) -> u64 {
+        (self.blocks - self.group_start(group)).min(self.blocks_per_group)
+    }
+
+    /// Block bitmap, inode bitmap and inode table location of `group`
+    fn metadata(&self, group: u64) -> (u64, u64, u64) {
+        let mut block = self.group_start(group);
+        if Self::has_superblock(group) {
+            block += 1 + self.descriptor_blocks;
+        }
+        (block, block + 1, block + 2)
+    }
+
+    /// Blocks at the start of `group` taken by metadata
+    fn overhead(&self, group: u64) -> u64 {
+        let (_, _, inode_table) = self.metadata(group);
+        inode_table + self.inode_table_blocks() - self.group_start(group)
+    }
+
+    fn compute(size: u64, blocks_per_group: u64) -> Result<Self, InstallError> {
+        let mut blocks = size / BLOCK_SIZE as u64;
+        let mut groups = blocks.div_ceil(blocks_per_group).max(1);
+        let per_group = blocks.min(blocks_per_group);
+        // One inode per 16 KiB, in whole inode table blocks
+        let inodes_per_group = ((per_group / 4) as u32).min(MAX_INODES_PER_GROUP) / INODES_PER_BLOCK * INODES_PER_BLOCK;
+        let mut geometry = Geometry {
+            blocks,
+            groups,
+            blocks_per_group,
+            inodes_per_group: inodes_per_group.max(INODES_PER_BLOCK),
+            descriptor_blocks: (groups as usize * DESCRIPTOR_SIZE).div_ceil(BLOCK_SIZE) as u64,
+        };
+        let last = groups - 1;
+        if groups > 1 && geometry.group_blocks(last) < geometry.overhead(last) + MIN_GROUP_DATA_BLOCKS {
+            groups -= 1;
+            blocks = groups * blocks_per_group;
+            geometry.groups = groups;
+            geometry.blocks = blocks;
+        }
+        if geometry.group_blocks(0) < geometry.overhead(0) + MIN_GROUP_DATA_BLOCKS {
+            return Err(InstallError::TooSmall {
+                needed: (geometry.overhead(0) + MIN_GROUP_DATA_BLOCKS) * BLOCK_SIZE as u64,
+                available: size,
+            });
+        }
+        Ok(geometry)
+    }
+}
+
+/// A file system to be written, with its first directories and files
+pub struct Ext2Builder {
+    uuid: [u8; 16],
+    label: String,
+    /// Seconds since the epoch for every timestamp
+    time: u32,
+    blocks_per_group: u64,
+    nodes: Vec<Node>,
+}
+
+fn inode_number(node: usize) -> u32 {
+    if node == 0 {
+        ROOT_INODE
+    } else {
+        FIRST_INODE - 1 + node as u32
+    }
+}
+
+fn put_u16(out: &mut [u8], offset: usize, value: u16) {
+    out[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut [u8], offset: usize, value: u32) {
+    out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn set_bits(bitmap: &mut [u8], range: core::ops::Range<usize>) {
+    for bit in range {
+        bitmap[bit / 8] |= 1 << (bit % 8);
+    }
+}
+
+impl Ext2Builder {
+    pub fn new(uuid: [u8; 16], label: &str, time: u32) -> Self {
+        let root = Node { name: String::new(), parent: 0, permissions: 0o755, kind: Kind::Directory };
+        let lost_found = Node { name: String::from("lost+found"), parent: 0, permissions: 0o700, kind: Kind::Directory };
+        Self { uuid, label: String::from(label), time, blocks_per_group: (BLOCK_SIZE * 8) as u64, nodes: vec![root, lost_found] }
+    }
+
+    fn child(&self, parent: usize, name: &str) -> Option<usize> {
+        (1..self.nodes.len()).find(|index| self.nodes[*index].parent == parent && self.nodes[*index].name == name)
+    }
+
+    /// Node of the directory at `path`, created with its parents
+    fn make_directory(&mut self, path: &str) -> usize {
+        let mut current = 0;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            current = match self.child(current, name) {
+                Some(index) => index,
+                None => {
+                    let node = Node { name: String::from(name), parent: current, permissions: 0o755, kind: Kind::Directory };
+                    self.nodes.push(node);
+                    self.nodes.len() - 1
+                }
+            };
+        }
+        current
+    }
+
+    /// Directory at `path` (relative to the root), created with its parents
+    pub fn directory(&mut self, path: &str) {
+        self.make_directory(path);
+    }
+
+    /// Regular file at `path`, created with its parent directories
+    pub fn file(&mut self, path: &str, permissions: u16, data: &[u8]) {
+        let (directory, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let parent = self.make_directory(directory);
+        let node = Node { name: String::from(name), parent, permissions, kind: Kind::File(data.to_vec()) };
+        match self.child(parent, name) {
+            Some(index) => self.nodes[index] = node,
+            None => self.nodes.push(node),
+        }
+    }
+
+    /// Data blocks of a node, and whether an indirect block comes first
+    fn data_blocks(node: &Node) -> Result<(usize, bool), InstallError> {
+        let blocks = match &node.kind {
+            Kind::Directory => 1,
+            Kind::File(data) => data.len().div_ceil(BLOCK_SIZE),
+        };
+        if blocks > DIRECT_BLOCKS + POINTERS_PER_BLOCK {
+            return Err(InstallError::FileTooLarge);
+        }
+        Ok((blocks, blocks > DIRECT_BLOCKS))
+    }
+
+    fn directory_block(&self, index: usize) -> Result<Vec<u8>, InstallError> {
+        let mut entries: Vec<(u32, &str, u8)> = vec![(inode_number(index), ".", FILE_TYPE_DIRECTORY)];
+        entries.push((inode_number(self.nodes[index].parent), "..", FILE_TYPE_DIRECTORY));
+        for (child, node) in self.nodes.iter().enumerate().skip(1) {
+            if node.parent == index {
+                let file_type = match node.kind {
+                    Kind::Directory => FILE_TYPE_DIRECTORY,
+                    Kind::File(_) => FILE_TYPE_REGULAR,
+                };
+                entries.push((inode_number(child), &node.name, file_type));
+            }
+        }
+
+        let mut block = vec![0u8; BLOCK_SIZE];
+        let mut offset = 0;
+        for (position, (inode, name, file_type)) in entries.iter().enumerate() {
+            let length = (8 + name.len()).next_multiple_of(4);
+            let record = if position == entries.len() - 1 { BLOCK_SIZE - offset } else { length };
+            if name.len() > 255 || offset + length > BLOCK_SIZE {
+                return Err(InstallError::FileTooLarge);
+            }
+            put_u32(&mut block, offset, *inode);
+            put_u16(&mut block, offset + 4, record as u16);
+            block[offset + 6] = name.len() as u8;
+            block[offset + 7] = *file_type;
+            block[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+            offset += length;
+        }
+        Ok(block)
+    }
+
+    fn encode_inode(&self, index: usize, first_block: u32, indirect: bool, blocks: usize) -> [u8; INODE_SIZE] {
+        let node = &self.nodes[index];
+        let mut inode = [0u8; INODE_SIZE];
+        let (mode, size, links) = match &node.kind {
+            Kind::Directory => {
+                let subdirectories = self.nodes.iter().skip(1).filter(|other| {
+                    other.parent == index && matches!(other.kind, Kind::Directory)
+                });
+                (MODE_DIRECTORY, BLOCK_SIZE as u32, 2 + subdirectories.count() as u16)
+            }
+            Kind::File(data) => (MODE_REGULAR, data.len() as u32, 1),
+        };
+        put_u16(&mut inode, 0, mode | node.permissions);
+        put_u32(&mut inode, 4, size);
+        for offset in [8, 12, 16] {
+            put_u32(&mut inode, offset, self.time);
+        }
+        put_u16(&mut inode, 26, links);
+        let sectors = (blocks + indirect as usize) * (BLOCK_SIZE / 512);
+        put_u32(&mut inode, 28, sectors as u32);
+        // Data follows the indirect block when there is one
+        let data = first_block + indirect as u32;
+        for slot in 0..blocks.min(DIRECT_BLOCKS) {
+            put_u32(&mut inode, 40 + slot * 4, data + slot as u32);
+        }
+        if indirect {
+            put_u32(&mut inode, 40 + DIRECT_BLOCKS * 4, first_block);
+        }
+        inode
+    }
+
+    fn superblock(&self, geometry: &Geometry, free_blocks: u64, free_inodes: u64, group: u64) -> Vec<u8> {
+        let mut block = vec![0u8; 1024];
+        put_u32(&mut block, 0, (geometry.groups * geometry.inodes_per_group as u64) as u32);
+        put_u32(&mut block, 4, geometry.blocks as u32);
+        put_u32(&mut block, 8, (geometry.blocks / 20) as u32);
+        put_u32(&mut block, 12, free_blocks as u32);
+        put_u32(&mut block, 16, free_inodes as u32);
+        put_u32(&mut block, 20, 0);
+        put_u32(&mut block, 24, LOG_BLOCK_SIZE);
+        put_u32(&mut block, 28, LOG_BLOCK_SIZE);
+        put_u32(&mut block, 32, geometry.blocks_per_group as u32);
+        put_u32(&mut block, 36, geometry.blocks_per_group as u32);
+        put_u32(&mut block, 40, geometry.inodes_per_group);
+        put_u32(&mut block, 48, self.time);
+        put_u16(&mut block, 54, 0xFFFF);
+        put_u16(&mut block, 56, MAGIC);
+        put_u16(&mut block, 58, STATE_CLEAN);
+        put_u16(&mut block, 60, ERRORS_CONTINUE);
+        put_u32(&mut block, 64, self.time);
+        put_u32(&mut block, 76, REVISION_DYNAMIC);
+        put_u32(&mut block, 84, FIRST_INODE);
+        put_u16(&mut block, 88, INODE_SIZE as u16);
+        put_u16(&mut block, 90, group as u16);
+        put_u32(&mut block, 96, FEATURE_INCOMPAT_FILETYPE);
+        put_u32(&mut block, 100, FEATURE_RO_COMPAT_SPARSE_SUPER);
+        block[104..120].copy_from_slice(&self.uuid);
+        let label = self.label.as_bytes();
+        block[120..120 + label.len().min(16)].copy_from_slice(&label[..label.len().min(16)]);
+        block
+    }
+
+    /// Format the `size` bytes at byte `offset` of `disk` and write the tree
+    pub fn write(&self, disk: &mut dyn Disk, offset: u64, size: u64) -> Result<(), InstallError> {
+        let geometry = Geometry::compute(size, self.blocks_per_group)?;
+        if inode_number(self.nodes.len() - 1) > geometry.inodes_per_group {
+            return Err(InstallError::FileTooLarge);
+        }
+        let at = |block: u64| offset + block * BLOCK_SIZE as u64;
+
+        // Blocks of group 0 after its metadata go to the tree, in node order
+        let mut next = geometry.overhead(0);
+        let mut placement = Vec::with_capacity(self.nodes.len());
+        for node in self.nodes.iter() {
+            let (blocks, indirect) = Self::data_blocks(node)?;
+            placement.push((next as u32, indirect, blocks));
+            next += (blocks + indirect as usize) as u64;
+        }
+        if next > geometry.group_blocks(0) {
+            return Err(InstallError::FileTooLarge);
+        }
+        let used_inodes = inode_number(self.nodes.len() - 1) as u64;
+        let directories = self.nodes.iter().filter(|node| matches!(node.kind, Kind::Directory)).count();
+
+        // Descriptors, and the bitmaps and zeroed inode table of each group
+        let mut descriptors = vec![0u8; geometry.descriptor_blocks as usize * BLOCK_SIZE];
+        let (mut free_blocks, mut free_inodes) = (0, 0);
+        let zeros = vec![0u8; 16 * BLOCK_SIZE];
+        for group in 0..geometry.groups {
+            let (block_bitmap, inode_bitmap, inode_table) = geometry.metadata(group);
+            let group_blocks = geometry.group_blocks(group) as usize;
+            let used_blocks = if group == 0 { next as usize } else { geometry.overhead(group) as usize };
+            let group_used_inodes = if group == 0 { used_inodes as usize } else { 0 };
+
+            let mut bitmap = vec![0u8; BLOCK_SIZE];
+            set_bits(&mut bitmap, 0..used_blocks);
+            set_bits(&mut bitmap, group_blocks..BLOCK_SIZE * 8);
+            write_at(disk, at(block_bitmap), &bitmap)?;
+            let mut bitmap = vec![0u8; BLOCK_SIZE];
+            set_bits(&mut bitmap, 0..group_used_inodes);
+            set_bits(&mut bitmap, geometry.inodes_per_group as usize..BLOCK_SIZE * 8);
+            write_at(disk, at(inode_bitmap), &bitmap)?;
+            let table_bytes = geometry.inode_table_blocks() as usize * BLOCK_SIZE;
+            for chunk in (0..table_bytes).step_by(zeros.len()) {
+                let length = zeros.len().min(table_bytes - chunk);
+                write_at(disk, at(inode_table) + chunk as u64, &zeros[..length])?;
+            }
+
+            let group_free_blocks = group_blocks - used_blocks;
+            let group_free_inodes = geometry.inodes_per_group as usize - group_used_inodes;
+            let descriptor = &mut descriptors[group as usize * DESCRIPTOR_SIZE..][..DESCRIPTOR_SIZE];
+            put_u32(descriptor, 0, block_bitmap as u32);
+            put_u32(descriptor, 4, inode_bitmap as u32);
+            put_u32(descriptor, 8, inode_table as u32);
+            put_u16(descriptor, 12, group_free_blocks as u16);
+            put_u16(descriptor, 14, group_free_inodes as u16);
+            put_u16(descriptor, 16, if group == 0 { directories as u16 } else { 0 });
+            free_blocks += group_free_blocks as u64;
+            free_inodes += group_free_inodes as u64;
+        }
+
+        // Superblock and descriptor copies; the primary superblock sits
+        // 1 KiB into block 0, the backups at the start of their group
+        for group in (0..geometry.groups).filter(|group| Geometry::has_superblock(*group)) {
+            let start = geometry.group_start(group);
+            let mut block = vec![0u8; BLOCK_SIZE];
+            let superblock = self.superblock(&geometry, free_blocks, free_inodes, group);
+            let position = if group == 0 { SUPERBLOCK_OFFSET } else { 0 };
+            block[position..position + superblock.len()].copy_from_slice(&superblock);
+            write_at(disk, at(start), &block)?;
+            write_at(disk, at(start + 1), &descriptors)?;
+        }
+
+        // Inodes of the tree, then its data
+        let (_, _, inode_table) = geometry.metadata(0);
+        let mut table = vec![0u8; (used_inodes as usize * INODE_SIZE).next_multiple_of(BLOCK_SIZE)];
+        for (index, (first_block, indirect, blocks)) in placement.iter().enumerate() {
+            let number = inode_number(index) as usize;
+            let inode = self.encode_inode(index, *first_block, *indirect, *blocks);
+            table[(number - 1) * INODE_SIZE..number * INODE_SIZE].copy_from_slice(&inode);
+        }
+        write_at(disk, at(inode_table), &table)?;
+
+        for (index, (first_block, indirect, blocks)) in placement.iter().enumerate() {
+            let mut block = *first_block as u64;
+            if *indirect {
+                let mut pointers = vec![0u8; BLOCK_SIZE];
+                for slot in DIRECT_BLOCKS..*blocks {
+                    let target = first_block + 1 + slot as u32;
+                    put_u32(&mut pointers, (slot - DIRECT_BLOCKS) * 4, target);
+                }
+                write_at(disk, at(block), &pointers)?;
+                block += 1;
+            }
+            match &self.nodes[index].kind {
+                Kind::Directory => write_at(disk, at(block), &self.directory_block(index)?)?,
+                Kind::File(data) if !data.is_empty() => {
+                    let mut padded = data.clone();
+                    padded.resize(blocks * BLOCK_SIZE, 0);
+                    write_at(disk, at(block), &padded)?;
+                }
+                Kind::File(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tests::MemoryDisk;
+    use crate::read_at;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_block(disk: &mut MemoryDisk, offset: u64, block: u32) -> Vec<u8> {
+        let mut data = vec![0u8; BLOCK_SIZE];
+        read_at(disk, offset + block as u64 * BLOCK_SIZE as u64, &mut data).unwrap();
+        data
+    }
+
+    fn read_inode(disk: &mut MemoryDisk, offset: u64, number: u32) -> Vec<u8> {
+        let superblock = read_block(disk, offset, 0)[SUPERBLOCK_OFFSET..].to_vec();
+        let inodes_per_group = u32_at(&superblock, 40);
+        let group = (number - 1) / inodes_per_group;
+        let descriptors = read_block(disk, offset, 1);
+        let table = u32_at(&descriptors, group as usize * DESCRIPTOR_SIZE + 8);
+        let index = ((number - 1) % inodes_per_group) as usize;
+        let block = read_block(disk, offset, table + (index / INODES_PER_BLOCK as usize) as u32);
+        block[(index % INODES_PER_BLOCK as usize) * INODE_SIZE..][..INODE_SIZE].to_vec()
+    }
+
+    fn inode_data(disk: &mut MemoryDisk, offset: u64, inode: &[u8]) -> Vec<u8> {
+        let size = u32_at(inode, 4) as usize;
+        let mut blocks: Vec<u32> = (0..DIRECT_BLOCKS).map(|slot| u32_at(inode, 40 + slot * 4)).collect();
+        let indirect = u32_at(inode, 40 + DIRECT_BLOCKS * 4);
+        if indirect != 0 {
+            let pointers = read_block(disk, offset, indirect);
+            blocks.extend((0..POINTERS_PER_BLOCK).map(|slot| u32_at(&pointers, slot * 4)));
+        }
+        let mut data = Vec::new();
+        for block in blocks.into_iter().take(size.div_ceil(BLOCK_SIZE)) {
+            data.extend_from_slice(&read_block(disk, offset, block));
+        }
+        data.truncate(size);
+        data
+    }
+
+    /// Contents of `path` in the file system at byte `offset`, the way a
+    /// driver would find them
+    pub fn read_file(disk: &mut MemoryDisk, offset: u64, path: &str) -> Option<Vec<u8>> {
+        let mut inode = read_inode(disk, offset, ROOT_INODE);
+        for name in path.split('/') {
+            let directory = inode_data(disk, offset, &inode);
+            let mut position = 0;
+            let mut found = None;
+            while position < directory.len() {
+                let length = directory[position + 6] as usize;
+                if &directory[position + 8..position + 8 + length] == name.as_bytes() {
+                    found = Some(u32_at(&directory, position));
+                    break;
+                }
+                position += u16_at(&directory, position + 4) as usize;
+            }
+            inode = read_inode(disk, offset, found?);
+        }
+        Some(inode_data(disk, offset, &inode))
+    }
+
+    #[test]
+    fn test_files_can_be_found_again() {
+        let mut disk = MemoryDisk::new(512, 9 << 20);
+        let mut builder = Ext2Builder::new([3; 16], "orion-data", 1_700_000_000);
+        builder.file("etc/orion/config/state.1", 0o600, b"state");
+        let large: Vec<u8> = (0..20 * BLOCK_SIZE + 5).map(|index| (index % 253) as u8).collect();
+        builder.file("etc/orion/large", 0o644, &large);
+        builder.file("etc/empty", 0o644, b"");
+        builder.directory("home");
+        builder.write(&mut disk, 1 << 20, 8 << 20).unwrap();
+
+        assert_eq!(read_file(&mut disk, 1 << 20, "etc/orion/config/state.1").unwrap(), b"state");
+        assert_eq!(read_file(&mut disk, 1 << 20, "etc/orion/large").unwrap(), large);
+        assert_eq!(read_file(&mut disk, 1 << 20, "etc/empty").unwrap(), b"");
+        assert!(read_file(&mut disk, 1 << 20, "etc/missing").is_none());
+
+        let superblock = read_block(&mut disk, 1 << 20, 0)[SUPERBLOCK_OFFSET..].to_vec();
+        assert_eq!(u16_at(&superblock, 56), MAGIC);
+        assert_eq!(u32_at(&superblock, 4), 2048);
+        assert_eq!(&superblock[120..130], b"orion-data");
+        // The root links "..", ".", etc, home and lost+found
+        let root = read_inode(&mut disk, 1 << 20, ROOT_INODE);
+        assert_eq!(u16_at(&root, 26), 5);
+        // Nothing was written before the partition
+        assert!(disk.data[..1 << 20].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_groups_get_sparse_superblock_copies() {
+        assert!([0, 1, 3, 5, 7, 9, 25, 27, 49].iter().all(|group| Geometry::has_superblock(*group)));
+        assert!(![2, 4, 6, 8, 10, 15].iter().any(|group| Geometry::has_superblock(*group)));
+
+        let mut disk = MemoryDisk::new(4096, 5 << 20);
+        let mut builder = Ext2Builder::new([4; 16], "data", 0);
+        builder.blocks_per_group = 256;
+        builder.file("hello", 0o644, b"hello");
+        builder.write(&mut disk, 0, 5 << 20).unwrap();
+        assert_eq!(read_file(&mut disk, 0, "hello").unwrap(), b"hello");
+
+        // 1280 blocks: groups 0 to 4, with backups in groups 1 and 3
+        let superblock = read_block(&mut disk, 0, 0)[SUPERBLOCK_OFFSET..].to_vec();
+        assert_eq!(u32_at(&superblock, 0) / u32_at(&superblock, 40), 5);
+        for group in [1u32, 3] {
+            let backup = read_block(&mut disk, 0, group * 256);
+            assert_eq!(u16_at(&backup, 56), MAGIC);
+            assert_eq!(u16_at(&backup, 90), group as u16);
+            assert_eq!(u32_at(&backup, 12), u32_at(&superblock, 12));
+        }
+        assert_ne!(u16_at(&read_block(&mut disk, 0, 2 * 256), 56), MAGIC);
+    }
+
+    #[test]
+    fn test_too_small_partitions_are_refused() {
+        let mut disk = MemoryDisk::new(512, 1 << 20);
+        let builder = Ext2Builder::new([0; 16], "", 0);
+        assert!(matches!(builder.write(&mut disk, 0, 256 << 10), Err(InstallError::TooSmall { .. })));
+    }
+}
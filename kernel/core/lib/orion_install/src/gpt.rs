@@ -0,0 +1,369 @@
+/*
+ * Orion Operating System - GUID Partition Table
+ *
+ * Writes and reads UEFI partition tables: a protective MBR in the first
+ * block, the primary header in the second followed by the entry array,
+ * and the backup entry array and header at the end of the disk, both
+ * headers protected by CRC32 as the firmware expects. The table always
+ * has 128 entries of 128 bytes, so the usable area starts and ends at the
+ * same place whatever the number of partitions.
+ *
+ * GUIDs are kept in their on-disk byte order: the first three fields are
+ * little-endian, the last two big-endian.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{read_at, write_at, Disk, InstallError};
+
+pub const ENTRY_COUNT: usize = 128;
+pub const ENTRY_SIZE: usize = 128;
+const ENTRIES_BYTES: usize = ENTRY_COUNT * ENTRY_SIZE;
+const HEADER_SIZE: usize = 92;
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+const REVISION: u32 = 0x0001_0000;
+/// UTF-16 code units of a partition name
+const NAME_UNITS: usize = 36;
+
+/// Partition attribute: the firmware must not touch the partition
+pub const ATTRIBUTE_REQUIRED: u64 = 1 << 0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub const ZERO: Guid = Guid([0; 16]);
+
+    /// GUID written as `a-b-c-d[0..2]-d[2..8]`
+    pub const fn from_fields(a: u32, b: u16, c: u16, d: [u8; 8]) -> Self {
+        let a = a.to_le_bytes();
+        let b = b.to_le_bytes();
+        let c = c.to_le_bytes();
+        Guid([a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]])
+    }
+
+    /// Version 4 GUID from random bytes
+    pub fn random(mut bytes: [u8; 16]) -> Self {
+        bytes[7] = (bytes[7] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Guid(bytes)
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9],
+            b[10],
+            b[11],
+            b[12],
+            b[13],
+            b[14],
+            b[15]
+        )
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// EFI system partition
+pub const ESP_TYPE: Guid = Guid::from_fields(0xC12A7328, 0xF81F, 0x11D2, [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B]);
+/// Orion boot control record (bootctl.rs)
+pub const BOOT_CONTROL_TYPE: Guid =
+    Guid::from_fields(0xB1E8D7A4, 0x3C5F, 0x4E69, [0x9A, 0x2B, 0x7F, 0x0C, 0x1D, 0x8E, 0x6A, 0x35]);
+/// Orion system slot, holding a system image
+pub const SYSTEM_TYPE: Guid = Guid::from_fields(0x6A3F1C52, 0x9B7E, 0x4D21, [0x8F, 0x0A, 0x2E, 0x5C, 0x7B, 0x9D, 0x40, 0x13]);
+/// Generic file system data, as other systems label ext2
+pub const DATA_TYPE: Guid = Guid::from_fields(0x0FC63DAF, 0x8483, 0x4772, [0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub type_guid: Guid,
+    pub guid: Guid,
+    pub first_lba: u64,
+    /// Inclusive
+    pub last_lba: u64,
+    pub attributes: u64,
+    pub name: String,
+}
+
+impl Partition {
+    pub fn blocks(&self) -> u64 {
+        self.last_lba + 1 - self.first_lba
+    }
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 { (value >> 1) ^ 0xEDB8_8320 } else { value >> 1 };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// Continue a CRC32 (IEEE, as in GPT and zlib) over `data`
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, byte| CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_guid(data: &[u8], offset: usize) -> Guid {
+    Guid(data[offset..offset + 16].try_into().unwrap())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionTable {
+    pub disk_guid: Guid,
+    pub block_size: u32,
+    /// Blocks of the whole disk
+    pub blocks: u64,
+    pub partitions: Vec<Partition>,
+}
+
+impl PartitionTable {
+    pub fn new(disk_guid: Guid, block_size: u32, blocks: u64) -> Self {
+        Self { disk_guid, block_size, blocks, partitions: Vec::new() }
+    }
+
+    fn entry_blocks(block_size: u32) -> u64 {
+        ENTRIES_BYTES.div_ceil(block_size as usize) as u64
+    }
+
+    /// First block a partition may use
+    pub fn first_usable(&self) -> u64 {
+        2 + Self::entry_blocks(self.block_size)
+    }
+
+    /// Last block a partition may use
+    pub fn last_usable(&self) -> u64 {
+        self.blocks - 2 - Self::entry_blocks(self.block_size)
+    }
+
+    fn encode_entries(&self) -> Vec<u8> {
+        let mut entries = vec![0u8; Self::entry_blocks(self.block_size) as usize * self.block_size as usize];
+        for (partition, entry) in self.partitions.iter().zip(entries.chunks_exact_mut(ENTRY_SIZE)) {
+            entry[0..16].copy_from_slice(&partition.type_guid.0);
+            entry[16..32].copy_from_slice(&partition.guid.0);
+            entry[32..40].copy_from_slice(&partition.first_lba.to_le_bytes());
+            entry[40..48].copy_from_slice(&partition.last_lba.to_le_bytes());
+            entry[48..56].copy_from_slice(&partition.attributes.to_le_bytes());
+            for (index, unit) in partition.name.encode_utf16().take(NAME_UNITS).enumerate() {
+                entry[56 + index * 2..58 + index * 2].copy_from_slice(&unit.to_le_bytes());
+            }
+        }
+        entries
+    }
+
+    fn encode_header(&self, current: u64, backup: u64, entries_lba: u64, entries_crc: u32) -> Vec<u8> {
+        let mut header = vec![0u8; self.block_size as usize];
+        header[0..8].copy_from_slice(SIGNATURE);
+        header[8..12].copy_from_slice(&REVISION.to_le_bytes());
+        header[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&current.to_le_bytes());
+        header[32..40].copy_from_slice(&backup.to_le_bytes());
+        header[40..48].copy_from_slice(&self.first_usable().to_le_bytes());
+        header[48..56].copy_from_slice(&self.last_usable().to_le_bytes());
+        header[56..72].copy_from_slice(&self.disk_guid.0);
+        header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&(ENTRY_COUNT as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&(ENTRY_SIZE as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        let crc = crc32(&header[..HEADER_SIZE]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        header
+    }
+
+    /// Protective MBR claiming the whole disk for GPT
+    fn encode_mbr(&self) -> Vec<u8> {
+        let mut mbr = vec![0u8; self.block_size as usize];
+        let entry = &mut mbr[446..462];
+        entry[1..4].copy_from_slice(&[0x00, 0x02, 0x00]);
+        entry[4] = 0xEE;
+        entry[5..8].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+        entry[8..12].copy_from_slice(&1u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&((self.blocks - 1).min(u32::MAX as u64) as u32).to_le_bytes());
+        mbr[510] = 0x55;
+        mbr[511] = 0xAA;
+        mbr
+    }
+
+    /// Write the MBR, both tables and both headers
+    pub fn write(&self, disk: &mut dyn Disk) -> Result<(), InstallError> {
+        let block_size = self.block_size as u64;
+        let entries = self.encode_entries();
+        let entries_crc = crc32(&entries[..ENTRIES_BYTES]);
+        let last = self.blocks - 1;
+        let backup_entries = last - Self::entry_blocks(self.block_size);
+
+        write_at(disk, 0, &self.encode_mbr())?;
+        write_at(disk, block_size, &self.encode_header(1, last, 2, entries_crc))?;
+        write_at(disk, 2 * block_size, &entries)?;
+        write_at(disk, backup_entries * block_size, &entries)?;
+        write_at(disk, last * block_size, &self.encode_header(last, 1, backup_entries, entries_crc))
+    }
+
+    /// Table described by the header at `lba`, if it and its entries are intact
+    fn read_copy(disk: &mut dyn Disk, lba: u64) -> Result<Option<Self>, InstallError> {
+        let block_size = disk.block_size();
+        let mut header = vec![0u8; block_size as usize];
+        read_at(disk, lba * block_size as u64, &mut header)?;
+        if &header[0..8] != SIGNATURE || read_u32(&header, 12) as usize != HEADER_SIZE {
+            return Ok(None);
+        }
+        let crc = read_u32(&header, 16);
+        header[16..20].fill(0);
+        if crc32(&header[..HEADER_SIZE]) != crc
+            || read_u32(&header, 80) as usize != ENTRY_COUNT
+            || read_u32(&header, 84) as usize != ENTRY_SIZE
+        {
+            return Ok(None);
+        }
+
+        let mut entries = vec![0u8; Self::entry_blocks(block_size) as usize * block_size as usize];
+        read_at(disk, read_u64(&header, 72) * block_size as u64, &mut entries)?;
+        if crc32(&entries[..ENTRIES_BYTES]) != read_u32(&header, 88) {
+            return Ok(None);
+        }
+        let mut table = Self::new(read_guid(&header, 56), block_size, disk.blocks());
+        for entry in entries[..ENTRIES_BYTES].chunks_exact(ENTRY_SIZE) {
+            let type_guid = read_guid(entry, 0);
+            if type_guid == Guid::ZERO {
+                continue;
+            }
+            let units: Vec<u16> = entry[56..128]
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .take_while(|unit| *unit != 0)
+                .collect();
+            table.partitions.push(Partition {
+                type_guid,
+                guid: read_guid(entry, 16),
+                first_lba: read_u64(entry, 32),
+                last_lba: read_u64(entry, 40),
+                attributes: read_u64(entry, 48),
+                name: String::from_utf16_lossy(&units),
+            });
+        }
+        Ok(Some(table))
+    }
+
+    /// The primary table, or the backup one when the primary is damaged
+    pub fn read(disk: &mut dyn Disk) -> Result<Self, InstallError> {
+        if let Some(table) = Self::read_copy(disk, 1)? {
+            return Ok(table);
+        }
+        let last = disk.blocks() - 1;
+        Self::read_copy(disk, last)?.ok_or(InstallError::NotFound)
+    }
+
+    /// First partition of type `type_guid` named `name`
+    pub fn find(&self, type_guid: Guid, name: &str) -> Option<&Partition> {
+        self.partitions.iter().find(|partition| partition.type_guid == type_guid && partition.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::MemoryDisk;
+
+    fn table(disk: &MemoryDisk) -> PartitionTable {
+        let mut table = PartitionTable::new(Guid::random([7; 16]), disk.block_size, disk.blocks());
+        table.partitions.push(Partition {
+            type_guid: ESP_TYPE,
+            guid: Guid::random([1; 16]),
+            first_lba: 2048,
+            last_lba: 4095,
+            attributes: ATTRIBUTE_REQUIRED,
+            name: String::from("EFI system partition"),
+        });
+        table.partitions.push(Partition {
+            type_guid: SYSTEM_TYPE,
+            guid: Guid::random([2; 16]),
+            first_lba: 4096,
+            last_lba: table.last_usable(),
+            attributes: 0,
+            name: String::from("system_a"),
+        });
+        table
+    }
+
+    #[test]
+    fn test_guids_follow_the_mixed_endian_layout() {
+        assert_eq!(alloc::format!("{}", ESP_TYPE), "c12a7328-f81f-11d2-ba4b-00a0c93ec93b");
+        assert_eq!(&ESP_TYPE.0[..4], &[0x28, 0x73, 0x2A, 0xC1]);
+        let guid = Guid::random([0xff; 16]);
+        assert_eq!(alloc::format!("{}", guid).as_bytes()[14], b'4');
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_tables_read_back_from_either_copy() {
+        let mut disk = MemoryDisk::new(512, 8 << 20);
+        let table = table(&disk);
+        table.write(&mut disk).unwrap();
+        assert_eq!(PartitionTable::read(&mut disk).unwrap(), table);
+        assert_eq!(&disk.data[510..512], &[0x55, 0xAA]);
+        assert_eq!(disk.data[446 + 4], 0xEE);
+        assert_eq!(table.first_usable(), 34);
+
+        // A damaged primary header falls back to the backup
+        disk.data[512 + 40] ^= 1;
+        let read = PartitionTable::read(&mut disk).unwrap();
+        assert_eq!(read.find(SYSTEM_TYPE, "system_a").unwrap().first_lba, 4096);
+
+        let mut blank = MemoryDisk::new(512, 1 << 20);
+        assert_eq!(PartitionTable::read(&mut blank), Err(InstallError::NotFound));
+    }
+
+    #[test]
+    fn test_large_sectors_keep_the_entry_array_in_whole_blocks() {
+        let mut disk = MemoryDisk::new(4096, 16 << 20);
+        let table = table(&disk);
+        assert_eq!(table.first_usable(), 6);
+        assert_eq!(table.last_usable(), disk.blocks() - 6);
+        table.write(&mut disk).unwrap();
+        assert_eq!(PartitionTable::read(&mut disk).unwrap().partitions, table.partitions);
+    }
+}
@@ -0,0 +1,489 @@
+/*
+ * Orion Operating System - Installer
+ *
+ * Lays Orion out on a whole disk:
+ *
+ *   EFI system partition   the bootloader and kernel, copied from the
+ *                          live medium's system partition image
+ *   boot_control           1 MiB, the boot control record (bootctl.rs)
+ *   system_a, system_b     system slots of the same size; the system
+ *                          image goes to system_a, system_b waits for
+ *                          the first update
+ *   data                   the rest of the disk, ext2 with the writable
+ *                          files, the configuration server state first
+ *
+ * Partitions start on 1 MiB boundaries. The images are copied in chunks
+ * with a CRC32 of each, read back and compared once all are written, so
+ * a disk that drops writes fails the installation instead of the first
+ * boot. The boot control record goes last: until it is written the disk
+ * does not boot, so an interrupted installation is simply run again.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::bootctl::{BootControl, BootSlot, SLOT_BOOTABLE, SLOT_SUCCESSFUL};
+use crate::config::initial_files;
+use crate::ext2::Ext2Builder;
+use crate::gpt::{
+    crc32_update, Guid, Partition, PartitionTable, ATTRIBUTE_REQUIRED, BOOT_CONTROL_TYPE, DATA_TYPE, ESP_TYPE,
+    SYSTEM_TYPE,
+};
+use crate::{read_at, write_at, Disk, InstallError};
+
+const MIB: u64 = 1 << 20;
+const ESP_MIN_SIZE: u64 = 64 * MIB;
+const BOOT_CONTROL_SIZE: u64 = MIB;
+/// System slots leave room for images growing with updates
+const SYSTEM_MIN_SIZE: u64 = 1024 * MIB;
+const DATA_MIN_SIZE: u64 = 256 * MIB;
+/// Bytes written, and checked on reading back, at a time
+const CHUNK_SIZE: usize = MIB as usize;
+/// Status of an image ending before its announced size
+const STATUS_EIO: i32 = -5;
+
+// Partition indexes in the layout
+pub const ESP: usize = 0;
+pub const BOOT_CONTROL: usize = 1;
+pub const SYSTEM_A: usize = 2;
+pub const SYSTEM_B: usize = 3;
+pub const DATA: usize = 4;
+
+/// Partition names, which the update tooling looks the slots up by
+pub const PARTITION_NAMES: [&str; 5] = ["EFI system partition", "boot_control", "system_a", "system_b", "data"];
+
+/// An image to copy. Errors are negative statuses
+pub trait Source {
+    fn size(&self) -> u64;
+    /// Read from `offset` into `buffer`, returning the bytes read (0 at the end)
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<usize, i32>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Partition,
+    Bootloader,
+    System,
+    Verify,
+    Data,
+    BootControl,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub table: PartitionTable,
+    /// UUID of the ext2 file system on the data partition
+    pub filesystem_uuid: [u8; 16],
+}
+
+impl Layout {
+    pub fn partition(&self, index: usize) -> &Partition {
+        &self.table.partitions[index]
+    }
+
+    /// Byte offset and size of a partition
+    pub fn extent(&self, index: usize) -> (u64, u64) {
+        let partition = self.partition(index);
+        let block_size = self.table.block_size as u64;
+        (partition.first_lba * block_size, partition.blocks() * block_size)
+    }
+}
+
+/// Partitions for a disk of `blocks` blocks receiving images of
+/// `esp_size` and `system_size` bytes; `random` draws GUIDs
+pub fn plan(
+    block_size: u32,
+    blocks: u64,
+    esp_size: u64,
+    system_size: u64,
+    random: &mut dyn FnMut() -> [u8; 16],
+) -> Result<Layout, InstallError> {
+    if !(512..=4096).contains(&block_size) || !block_size.is_power_of_two() {
+        return Err(InstallError::BlockSize(block_size));
+    }
+    let block_size_bytes = block_size as u64;
+    let mut table = PartitionTable::new(Guid::random(random()), block_size, blocks);
+    let system_size = (system_size * 2).max(SYSTEM_MIN_SIZE).next_multiple_of(MIB);
+    let sizes = [esp_size.max(ESP_MIN_SIZE).next_multiple_of(MIB), BOOT_CONTROL_SIZE, system_size, system_size];
+    let types = [ESP_TYPE, BOOT_CONTROL_TYPE, SYSTEM_TYPE, SYSTEM_TYPE];
+
+    let needed = MIB + sizes.iter().sum::<u64>() + DATA_MIN_SIZE + MIB;
+    let available = blocks * block_size_bytes;
+    if available < needed {
+        return Err(InstallError::TooSmall { needed, available });
+    }
+
+    let mut next = MIB / block_size_bytes;
+    for (index, (size, type_guid)) in sizes.iter().zip(types).enumerate() {
+        let count = size / block_size_bytes;
+        table.partitions.push(Partition {
+            type_guid,
+            guid: Guid::random(random()),
+            first_lba: next,
+            last_lba: next + count - 1,
+            attributes: if index == BOOT_CONTROL { ATTRIBUTE_REQUIRED } else { 0 },
+            name: String::from(PARTITION_NAMES[index]),
+        });
+        next += count;
+    }
+    // The data partition ends on a MiB boundary before the backup table
+    let align = MIB / block_size_bytes;
+    let last = (table.last_usable() + 1) / align * align - 1;
+    table.partitions.push(Partition {
+        type_guid: DATA_TYPE,
+        guid: Guid::random(random()),
+        first_lba: next,
+        last_lba: last,
+        attributes: 0,
+        name: String::from(PARTITION_NAMES[DATA]),
+    });
+    Ok(Layout { table, filesystem_uuid: random() })
+}
+
+/// What goes on the disk besides the partitions themselves
+pub struct Images<'a> {
+    /// Image of the EFI system partition of the live medium
+    pub esp: &'a mut dyn Source,
+    pub system: &'a mut dyn Source,
+    /// Configuration server document made version 1
+    pub config: &'a [u8],
+    /// Kernel command line after the root and data partitions
+    pub cmdline: &'a str,
+}
+
+/// An image arriving in pieces, written to a partition in chunks with a
+/// CRC32 of each so the disk can be read back and compared afterwards
+pub struct ImageWriter {
+    offset: u64,
+    capacity: u64,
+    written: u64,
+    chunk: Vec<u8>,
+    checksums: Vec<u32>,
+}
+
+impl ImageWriter {
+    /// Image going to the `capacity` bytes at byte `offset` of the disk
+    pub fn new(offset: u64, capacity: u64) -> Self {
+        Self { offset, capacity, written: 0, chunk: Vec::with_capacity(CHUNK_SIZE), checksums: Vec::new() }
+    }
+
+    /// Bytes of the image received so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn write(&mut self, disk: &mut dyn Disk, mut data: &[u8]) -> Result<(), InstallError> {
+        let needed = self.written + data.len() as u64;
+        if needed > self.capacity {
+            return Err(InstallError::TooSmall { needed, available: self.capacity });
+        }
+        while !data.is_empty() {
+            let length = (CHUNK_SIZE - self.chunk.len()).min(data.len());
+            self.chunk.extend_from_slice(&data[..length]);
+            self.written += length as u64;
+            data = &data[length..];
+            if self.chunk.len() == CHUNK_SIZE {
+                self.write_chunk(disk)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, disk: &mut dyn Disk) -> Result<(), InstallError> {
+        let start = self.checksums.len() as u64 * CHUNK_SIZE as u64;
+        let padded = self.chunk.len().next_multiple_of(disk.block_size() as usize);
+        self.chunk.resize(padded, 0);
+        self.checksums.push(crc32_update(0, &self.chunk));
+        write_at(disk, self.offset + start, &self.chunk)?;
+        self.chunk.clear();
+        Ok(())
+    }
+
+    /// Write the last partial chunk and flush the disk
+    pub fn finish(&mut self, disk: &mut dyn Disk) -> Result<(), InstallError> {
+        if !self.chunk.is_empty() {
+            self.write_chunk(disk)?;
+        }
+        disk.flush().map_err(InstallError::Disk)
+    }
+
+    /// Read back what was written and compare the chunk checksums;
+    /// `progress` hears the image bytes checked by each step
+    pub fn verify(&self, disk: &mut dyn Disk, progress: &mut dyn FnMut(u64)) -> Result<(), InstallError> {
+        let block_size = disk.block_size() as u64;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        for (index, expected) in self.checksums.iter().enumerate() {
+            let start = index as u64 * CHUNK_SIZE as u64;
+            let length = (self.written - start).min(CHUNK_SIZE as u64);
+            let padded = length.next_multiple_of(block_size) as usize;
+            read_at(disk, self.offset + start, &mut buffer[..padded])?;
+            if crc32_update(0, &buffer[..padded]) != *expected {
+                return Err(InstallError::Verify(self.offset + start));
+            }
+            progress(length);
+        }
+        Ok(())
+    }
+}
+
+pub struct Installer<'a> {
+    disk: &'a mut dyn Disk,
+    progress: &'a mut dyn FnMut(Stage, u64, u64),
+}
+
+impl<'a> Installer<'a> {
+    /// `progress` hears the stage, the bytes (or steps) done and the total
+    pub fn new(disk: &'a mut dyn Disk, progress: &'a mut dyn FnMut(Stage, u64, u64)) -> Self {
+        Self { disk, progress }
+    }
+
+    /// Copy `source` to the partition at `offset`
+    fn copy(&mut self, stage: Stage, source: &mut dyn Source, offset: u64, capacity: u64) -> Result<ImageWriter, InstallError> {
+        let total = source.size();
+        let mut writer = ImageWriter::new(offset, capacity);
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        (self.progress)(stage, 0, total);
+        while writer.written() < total {
+            let done = writer.written();
+            let length = (total - done).min(CHUNK_SIZE as u64) as usize;
+            let count = match source.read(done, &mut buffer[..length]) {
+                Ok(0) => return Err(InstallError::Source(STATUS_EIO)),
+                Ok(count) => count,
+                Err(status) => return Err(InstallError::Source(status)),
+            };
+            writer.write(self.disk, &buffer[..count])?;
+            (self.progress)(stage, writer.written(), total);
+        }
+        writer.finish(self.disk)?;
+        Ok(writer)
+    }
+
+    /// Install onto the disk following `layout`; `time` is the realtime
+    /// clock in nanoseconds. Returns the boot control record written
+    pub fn install(&mut self, layout: &Layout, images: &mut Images, time: u64) -> Result<BootControl, InstallError> {
+        let (esp, esp_size) = layout.extent(ESP);
+        let (system, system_size) = layout.extent(SYSTEM_A);
+        if images.esp.size() > esp_size || images.system.size() > system_size {
+            let needed = images.esp.size().max(images.system.size());
+            return Err(InstallError::TooSmall { needed, available: esp_size.min(system_size) });
+        }
+
+        // Leave no earlier boot control record behind while copying
+        (self.progress)(Stage::Partition, 0, 1);
+        layout.table.write(self.disk)?;
+        let (control, _) = layout.extent(BOOT_CONTROL);
+        write_at(self.disk, control, &vec![0u8; 2 * 4096])?;
+        (self.progress)(Stage::Partition, 1, 1);
+
+        let esp_image = self.copy(Stage::Bootloader, images.esp, esp, esp_size)?;
+        let system_image = self.copy(Stage::System, images.system, system, system_size)?;
+        let total = images.esp.size() + images.system.size();
+        let mut done = 0;
+        let progress = &mut self.progress;
+        for image in [&esp_image, &system_image] {
+            image.verify(self.disk, &mut |length| {
+                done += length;
+                progress(Stage::Verify, done, total);
+            })?;
+        }
+
+        (self.progress)(Stage::Data, 0, 1);
+        let mut builder = Ext2Builder::new(layout.filesystem_uuid, "orion-data", (time / 1_000_000_000) as u32);
+        for (path, contents) in initial_files(images.config, time) {
+            builder.file(&path, 0o600, &contents);
+        }
+        builder.directory("home");
+        let (data, data_size) = layout.extent(DATA);
+        builder.write(self.disk, data, data_size)?;
+        self.disk.flush().map_err(InstallError::Disk)?;
+        (self.progress)(Stage::Data, 1, 1);
+
+        // The installed slot has nothing to fall back to, so it starts out
+        // as good as a slot that completed a boot
+        (self.progress)(Stage::BootControl, 0, 1);
+        let mut slot_a = BootSlot::new(layout.partition(SYSTEM_A));
+        slot_a.flags = SLOT_BOOTABLE | SLOT_SUCCESSFUL;
+        let mut record = BootControl {
+            sequence: 0,
+            active_slot: 0,
+            slots: [slot_a, BootSlot::new(layout.partition(SYSTEM_B))],
+            data_guid: layout.partition(DATA).guid,
+            cmdline: String::from(images.cmdline),
+        };
+        record.write(self.disk, layout.partition(BOOT_CONTROL))?;
+        (self.progress)(Stage::BootControl, 1, 1);
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::EMPTY_DOCUMENT;
+    use crate::ext2::tests::read_file;
+    use crate::tests::MemoryDisk;
+    use alloc::collections::BTreeMap;
+
+    /// Image of generated bytes
+    pub(crate) struct Pattern(pub u64, pub u8);
+
+    impl Source for Pattern {
+        fn size(&self) -> u64 {
+            self.0
+        }
+
+        fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<usize, i32> {
+            // Short reads, as from a file server
+            let count = buffer.len().min(3000).min((self.0 - offset) as usize);
+            for (index, byte) in buffer[..count].iter_mut().enumerate() {
+                *byte = ((offset as usize + index) % 199) as u8 ^ self.1;
+            }
+            Ok(count)
+        }
+    }
+
+    /// Disk of a few GiB keeping only the blocks that are not zero
+    pub(crate) struct SparseDisk {
+        blocks: u64,
+        data: BTreeMap<u64, Vec<u8>>,
+        /// Writes to this block are lost
+        drop: Option<u64>,
+    }
+
+    impl SparseDisk {
+        pub(crate) fn new(blocks: u64) -> Self {
+            Self { blocks, data: BTreeMap::new(), drop: None }
+        }
+    }
+
+    impl Disk for SparseDisk {
+        fn block_size(&self) -> u32 {
+            512
+        }
+
+        fn blocks(&self) -> u64 {
+            self.blocks
+        }
+
+        fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32> {
+            for (index, block) in buffer.chunks_mut(512).enumerate() {
+                match self.data.get(&(lba + index as u64)) {
+                    Some(data) => block.copy_from_slice(data),
+                    None => block.fill(0),
+                }
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, lba: u64, data: &[u8]) -> Result<(), i32> {
+            for (index, block) in data.chunks(512).enumerate() {
+                let lba = lba + index as u64;
+                if Some(lba) == self.drop {
+                    continue;
+                }
+                if block.iter().all(|byte| *byte == 0) {
+                    self.data.remove(&lba);
+                } else {
+                    self.data.insert(lba, block.to_vec());
+                }
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), i32> {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn counter() -> impl FnMut() -> [u8; 16] {
+        let mut next = 0u8;
+        move || {
+            next += 1;
+            [next; 16]
+        }
+    }
+
+    #[test]
+    fn test_plans_aligned_partitions() {
+        let blocks = (4u64 << 30) / 512;
+        let layout = plan(512, blocks, 20 * MIB, 300 * MIB, &mut counter()).unwrap();
+        let names: Vec<&str> = layout.table.partitions.iter().map(|partition| partition.name.as_str()).collect();
+        assert_eq!(names, PARTITION_NAMES);
+        for partition in layout.table.partitions.iter() {
+            assert_eq!(partition.first_lba % 2048, 0);
+            assert_eq!((partition.last_lba + 1) % 2048, 0);
+        }
+        assert_eq!(layout.extent(ESP), (MIB, 64 * MIB));
+        assert_eq!(layout.extent(SYSTEM_A).1, 1024 * MIB);
+        assert!(layout.partition(DATA).last_lba <= layout.table.last_usable());
+
+        let error = plan(512, (2u64 << 30) / 512, 20 * MIB, 300 * MIB, &mut counter()).unwrap_err();
+        assert!(matches!(error, InstallError::TooSmall { .. }));
+        assert_eq!(plan(520, blocks, 0, 0, &mut counter()), Err(InstallError::BlockSize(520)));
+    }
+
+    type Report = (Stage, u64, u64);
+
+    fn install(disk: &mut SparseDisk) -> Result<(Layout, Vec<Report>), InstallError> {
+        let layout = plan(512, disk.blocks, 3 * MIB, 5 * MIB / 2, &mut counter()).unwrap();
+        let mut reports = Vec::new();
+        let mut progress = |stage, done, total| reports.push((stage, done, total));
+        let (mut esp, mut system) = (Pattern(3 * MIB, 0x5a), Pattern(5 * MIB / 2 + 100, 0));
+        let mut images =
+            Images { esp: &mut esp, system: &mut system, config: EMPTY_DOCUMENT.as_bytes(), cmdline: "quiet" };
+        Installer::new(disk, &mut progress).install(&layout, &mut images, 5_000_000_000)?;
+        Ok((layout, reports))
+    }
+
+    #[test]
+    fn test_installs_a_bootable_disk() {
+        let mut disk = SparseDisk::new((3u64 << 30) / 512);
+        let (layout, reports) = install(&mut disk).unwrap();
+
+        assert_eq!(PartitionTable::read(&mut disk).unwrap(), layout.table);
+        let record = BootControl::read(&mut disk, layout.partition(BOOT_CONTROL)).unwrap();
+        assert_eq!(record.slots[0].guid, layout.partition(SYSTEM_A).guid);
+        assert_eq!(record.slots[0].flags, SLOT_BOOTABLE | SLOT_SUCCESSFUL);
+        assert_eq!(record.slots[1].flags, 0);
+        assert_eq!(record.cmdline, "quiet");
+
+        let (system, _) = layout.extent(SYSTEM_A);
+        let mut copied = vec![0u8; 512];
+        read_at(&mut disk, system + 512, &mut copied).unwrap();
+        let mut expected = vec![0u8; 512];
+        Pattern(MIB, 0).read(512, &mut expected).unwrap();
+        assert_eq!(copied, expected);
+
+        assert!(reports.contains(&(Stage::System, 5 * MIB / 2 + 100, 5 * MIB / 2 + 100)));
+        assert_eq!(reports.iter().rfind(|(stage, _, _)| *stage == Stage::Verify).unwrap().1, 11 * MIB / 2 + 100);
+        assert_eq!(reports.last(), Some(&(Stage::BootControl, 1, 1)));
+
+        // The configuration server finds its state on the data partition
+        let (data, data_size) = layout.extent(DATA);
+        let mut memory = MemoryDisk::new(512, 8 << 20);
+        let mut buffer = vec![0u8; 8 << 20];
+        read_at(&mut disk, data, &mut buffer).unwrap();
+        memory.data.copy_from_slice(&buffer);
+        let document = read_file(&mut memory, 0, "etc/orion/config/version.1").unwrap();
+        assert_eq!(&document[20..], EMPTY_DOCUMENT.as_bytes());
+        assert!(data_size > DATA_MIN_SIZE);
+    }
+
+    #[test]
+    fn test_lost_writes_fail_the_installation() {
+        let mut disk = SparseDisk::new((3u64 << 30) / 512);
+        let layout = plan(512, disk.blocks, 3 * MIB, 5 * MIB / 2, &mut counter()).unwrap();
+        disk.drop = Some(layout.partition(SYSTEM_A).first_lba + 2048 + 7);
+        let (system, _) = layout.extent(SYSTEM_A);
+        assert_eq!(install(&mut disk).unwrap_err(), InstallError::Verify(system + MIB));
+        // No boot control record: the disk does not boot a partial system
+        assert_eq!(BootControl::read(&mut disk, layout.partition(BOOT_CONTROL)), Err(InstallError::NotFound));
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Orion Operating System - System Installation
+ *
+ * What it takes to put Orion on an empty disk from the live system: a GPT
+ * partition table (gpt.rs), an ext2 file system for the writable files
+ * (ext2.rs), the boot control record the bootloader reads to pick the
+ * system slot (bootctl.rs), the first state of the configuration server
+ * (config.rs), and the installer tying them together with progress
+ * reporting (installer.rs). Updates of an installed system go to the
+ * system slot not running (update.rs).
+ *
+ * Everything works on a Disk, a whole device addressed in logical blocks;
+ * the orion-install tool backs it with the raw disk requests of the block
+ * drivers, tests with memory.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod bootctl;
+pub mod config;
+pub mod ext2;
+pub mod gpt;
+pub mod installer;
+pub mod update;
+
+pub use bootctl::{BootControl, BootSlot};
+pub use ext2::Ext2Builder;
+pub use gpt::{Guid, Partition, PartitionTable};
+pub use installer::{plan, ImageWriter, Images, Installer, Layout, Source, Stage};
+pub use update::SystemDisk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallError {
+    /// The disk failed a request with this status
+    Disk(i32),
+    /// Reading an image failed with this status
+    Source(i32),
+    /// The disk is smaller than the layout needs, both in bytes
+    TooSmall { needed: u64, available: u64 },
+    /// Logical block size the installer cannot lay out
+    BlockSize(u32),
+    ReadOnly,
+    /// What was read back differs from what was written, at this byte
+    Verify(u64),
+    /// A file does not fit the file system being built
+    FileTooLarge,
+    /// No valid partition table or boot control record
+    NotFound,
+}
+
+/// A whole disk, addressed in logical blocks. Errors are negative statuses
+pub trait Disk {
+    fn block_size(&self) -> u32;
+    fn blocks(&self) -> u64;
+    /// Fill `buffer`, a whole number of blocks, from `lba` on
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32>;
+    /// Write `data`, a whole number of blocks, from `lba` on
+    fn write(&mut self, lba: u64, data: &[u8]) -> Result<(), i32>;
+    fn flush(&mut self) -> Result<(), i32>;
+
+    fn size(&self) -> u64 {
+        self.blocks() * self.block_size() as u64
+    }
+}
+
+/// Write `data` at byte `offset`; both must fall on block boundaries
+pub fn write_at(disk: &mut dyn Disk, offset: u64, data: &[u8]) -> Result<(), InstallError> {
+    let block_size = disk.block_size() as u64;
+    debug_assert!(offset.is_multiple_of(block_size) && (data.len() as u64).is_multiple_of(block_size));
+    disk.write(offset / block_size, data).map_err(InstallError::Disk)
+}
+
+/// Read `buffer.len()` bytes at byte `offset`; both on block boundaries
+pub fn read_at(disk: &mut dyn Disk, offset: u64, buffer: &mut [u8]) -> Result<(), InstallError> {
+    let block_size = disk.block_size() as u64;
+    debug_assert!(offset.is_multiple_of(block_size) && (buffer.len() as u64).is_multiple_of(block_size));
+    disk.read(offset / block_size, buffer).map_err(InstallError::Disk)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::Disk;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Disk kept in memory
+    pub struct MemoryDisk {
+        pub block_size: u32,
+        pub data: Vec<u8>,
+        pub flushes: usize,
+    }
+
+    impl MemoryDisk {
+        pub fn new(block_size: u32, size: usize) -> Self {
+            Self { block_size, data: vec![0; size], flushes: 0 }
+        }
+    }
+
+    impl Disk for MemoryDisk {
+        fn block_size(&self) -> u32 {
+            self.block_size
+        }
+
+        fn blocks(&self) -> u64 {
+            (self.data.len() / self.block_size as usize) as u64
+        }
+
+        fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32> {
+            let start = lba as usize * self.block_size as usize;
+            let source = self.data.get(start..start + buffer.len()).ok_or(-22)?;
+            buffer.copy_from_slice(source);
+            Ok(())
+        }
+
+        fn write(&mut self, lba: u64, data: &[u8]) -> Result<(), i32> {
+            let start = lba as usize * self.block_size as usize;
+            self.data.get_mut(start..start + data.len()).ok_or(-22)?.copy_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), i32> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Orion Operating System - System Updates
+ *
+ * An installed disk has two system slots (installer.rs). An update is
+ * written to the slot not running, read back, and only then made the
+ * active slot with a few tries to complete a boot; until one completes,
+ * the bootloader falls back to the slot that ran before (bootctl.rs).
+ *
+ * SystemDisk finds the slots through the boot control partition of the
+ * disk; the slot being written is taken out of use first, so a write cut
+ * short never leaves a bootable half image behind.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::bootctl::{BootControl, SLOT_BOOTABLE, SLOT_SUCCESSFUL};
+use crate::gpt::{Partition, PartitionTable, BOOT_CONTROL_TYPE, SYSTEM_TYPE};
+use crate::installer::{ImageWriter, BOOT_CONTROL, PARTITION_NAMES};
+use crate::{Disk, InstallError};
+
+/// An installed disk and its boot control record
+pub struct SystemDisk {
+    pub table: PartitionTable,
+    pub control: Partition,
+    pub record: BootControl,
+    /// Slot running now: the active slot when the disk was opened, since
+    /// the bootloader makes the slot it starts the active one
+    pub booted: usize,
+}
+
+impl SystemDisk {
+    /// NotFound unless `disk` is partitioned by the installer
+    pub fn open(disk: &mut dyn Disk) -> Result<Self, InstallError> {
+        let table = PartitionTable::read(disk)?;
+        let control = table.find(BOOT_CONTROL_TYPE, PARTITION_NAMES[BOOT_CONTROL]).ok_or(InstallError::NotFound)?.clone();
+        let record = BootControl::read(disk, &control)?;
+        let booted = record.active_slot as usize;
+        Ok(Self { table, control, record, booted })
+    }
+
+    /// System partition of `slot`
+    pub fn slot(&self, slot: usize) -> Result<&Partition, InstallError> {
+        let guid = self.record.slots[slot].guid;
+        self.table
+            .partitions
+            .iter()
+            .find(|partition| partition.type_guid == SYSTEM_TYPE && partition.guid == guid)
+            .ok_or(InstallError::NotFound)
+    }
+
+    /// Slot updates go to
+    pub fn inactive(&self) -> usize {
+        1 - self.booted
+    }
+
+    pub fn save(&mut self, disk: &mut dyn Disk) -> Result<(), InstallError> {
+        self.record.write(disk, &self.control)
+    }
+
+    /// Take the inactive slot out of use and start writing an image of
+    /// `size` bytes to it
+    pub fn begin_update(&mut self, disk: &mut dyn Disk, size: u64) -> Result<ImageWriter, InstallError> {
+        let slot = self.inactive();
+        let partition = self.slot(slot)?;
+        let block_size = disk.block_size() as u64;
+        let (offset, capacity) = (partition.first_lba * block_size, partition.blocks() * block_size);
+        if size > capacity {
+            return Err(InstallError::TooSmall { needed: size, available: capacity });
+        }
+        if self.record.slots[slot].flags != 0 {
+            self.record.mark_unbootable(slot);
+            self.save(disk)?;
+        }
+        Ok(ImageWriter::new(offset, capacity))
+    }
+
+    /// Check the image written and boot it from the next start on, with
+    /// `tries` boots to complete one
+    pub fn commit_update(
+        &mut self,
+        disk: &mut dyn Disk,
+        writer: &mut ImageWriter,
+        tries: u32,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<(), InstallError> {
+        writer.finish(disk)?;
+        writer.verify(disk, progress)?;
+        self.record.activate(self.inactive(), tries.max(1));
+        self.save(disk)
+    }
+
+    /// The boot from the running slot completed. False when it was known
+    /// to boot already
+    pub fn confirm_boot(&mut self, disk: &mut dyn Disk) -> Result<bool, InstallError> {
+        let slot = &self.record.slots[self.booted];
+        if slot.flags & SLOT_BOOTABLE == 0 || slot.flags & SLOT_SUCCESSFUL != 0 {
+            return Ok(false);
+        }
+        self.record.mark_successful(self.booted);
+        self.save(disk)?;
+        Ok(true)
+    }
+
+    /// The boot from the running slot failed: the next start goes back to
+    /// the other slot
+    pub fn fail_boot(&mut self, disk: &mut dyn Disk) -> Result<(), InstallError> {
+        self.record.mark_unbootable(self.booted);
+        self.save(disk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EMPTY_DOCUMENT;
+    use crate::installer::tests::{counter, Pattern, SparseDisk};
+    use crate::installer::{plan, Images, Installer};
+    use alloc::vec::Vec;
+
+    fn installed() -> SparseDisk {
+        let mut disk = SparseDisk::new((3u64 << 30) / 512);
+        let layout = plan(512, disk.blocks(), 1 << 20, 1 << 20, &mut counter()).unwrap();
+        let (mut esp, mut system) = (Pattern(1 << 20, 1), Pattern(1 << 20, 2));
+        let mut images = Images { esp: &mut esp, system: &mut system, config: EMPTY_DOCUMENT.as_bytes(), cmdline: "" };
+        Installer::new(&mut disk, &mut |_, _, _| {}).install(&layout, &mut images, 0).unwrap();
+        disk
+    }
+
+    /// Bootloader start: the slot selected and the record stored
+    fn boot(disk: &mut SparseDisk) -> usize {
+        let mut system = SystemDisk::open(disk).unwrap();
+        let slot = system.record.select().unwrap();
+        system.save(disk).unwrap();
+        slot
+    }
+
+    fn update(disk: &mut SparseDisk, tries: u32) {
+        let mut system = SystemDisk::open(disk).unwrap();
+        let mut writer = system.begin_update(disk, 3 << 20).unwrap();
+        let image: Vec<u8> = (0..3u32 << 20).map(|index| (index % 251) as u8).collect();
+        for chunk in image.chunks(100_000) {
+            writer.write(disk, chunk).unwrap();
+        }
+        system.commit_update(disk, &mut writer, tries, &mut |_| {}).unwrap();
+    }
+
+    #[test]
+    fn test_confirmed_updates_stay() {
+        let mut disk = installed();
+        update(&mut disk, 1);
+        assert_eq!(boot(&mut disk), 1);
+        let mut system = SystemDisk::open(&mut disk).unwrap();
+        assert!(system.confirm_boot(&mut disk).unwrap());
+        assert!(!system.confirm_boot(&mut disk).unwrap());
+        assert_eq!(boot(&mut disk), 1);
+
+        // The next update goes back to slot 0
+        update(&mut disk, 1);
+        let system = SystemDisk::open(&mut disk).unwrap();
+        assert_eq!(system.record.active_slot, 0);
+        assert_eq!(system.record.slots[1].flags, SLOT_BOOTABLE | SLOT_SUCCESSFUL);
+    }
+
+    #[test]
+    fn test_unconfirmed_updates_roll_back() {
+        let mut disk = installed();
+        update(&mut disk, 1);
+        assert_eq!(boot(&mut disk), 1);
+        // No confirmation: the try is spent and the next start falls back
+        assert_eq!(boot(&mut disk), 0);
+        let system = SystemDisk::open(&mut disk).unwrap();
+        assert_eq!(system.record.slots[1].flags, 0);
+
+        // Failing the boot outright has the same effect
+        update(&mut disk, 3);
+        assert_eq!(boot(&mut disk), 1);
+        let mut system = SystemDisk::open(&mut disk).unwrap();
+        system.fail_boot(&mut disk).unwrap();
+        assert_eq!(boot(&mut disk), 0);
+    }
+
+    #[test]
+    fn test_interrupted_updates_leave_no_bootable_slot() {
+        let mut disk = installed();
+        update(&mut disk, 1);
+        assert_eq!(boot(&mut disk), 1);
+        SystemDisk::open(&mut disk).unwrap().confirm_boot(&mut disk).unwrap();
+
+        let mut system = SystemDisk::open(&mut disk).unwrap();
+        let mut writer = system.begin_update(&mut disk, 3 << 20).unwrap();
+        writer.write(&mut disk, &[1; 4096]).unwrap();
+        // Power lost here
+        let system = SystemDisk::open(&mut disk).unwrap();
+        assert_eq!(system.record.slots[0].flags, 0);
+        assert_eq!(boot(&mut disk), 1);
+
+        let mut system = SystemDisk::open(&mut disk).unwrap();
+        let error = system.begin_update(&mut disk, 4 << 30).err();
+        assert!(matches!(error, Some(InstallError::TooSmall { .. })));
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Orion Operating System - MAC Denials and Learning
+ *
+ * The kernel keeps one record per subject label, object label and class
+ * denied since the policy was loaded, read with MAC_CTL_DENIAL
+ * (mac_denial_t):
+ *
+ *   0    4   subject label
+ *   4    4   object label
+ *   8    4   object class
+ *   12   4   permissions denied
+ *   16   8   denials
+ *   24   32  subject label name, NUL padded
+ *   56   32  object label name
+ *   88   96  first object denied
+ *
+ * Learning turns the records into the `allow` lines that would have let
+ * the accesses through, to be reviewed and added to the policy. A line
+ * naming `unlabeled` usually means a context is missing rather than a
+ * rule.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{class_name, perm_names, LABEL_NAME_MAX, PATTERN_MAX};
+
+pub const DENIAL_SIZE: usize = 24 + 2 * LABEL_NAME_MAX + PATTERN_MAX;
+pub const STATUS_SIZE: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Denial {
+    pub subject: String,
+    pub object: String,
+    pub class: u32,
+    pub perms: u32,
+    pub count: u64,
+    pub example: String,
+}
+
+/// MAC_CTL_STATUS (mac_status_t)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub mode: u32,
+    pub labels: u32,
+    pub rules: u32,
+    /// Processes carrying a label
+    pub processes: u32,
+    pub checks: u64,
+    /// Denials since boot, enforced or only recorded
+    pub denials: u64,
+    /// Policies loaded since boot
+    pub generation: u64,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_name(data: &[u8]) -> String {
+    let end = data.iter().position(|&byte| byte == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+impl Denial {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < DENIAL_SIZE {
+            return None;
+        }
+        let names = 24;
+        Some(Denial {
+            class: read_u32(data, 8),
+            perms: read_u32(data, 12),
+            count: read_u64(data, 16),
+            subject: read_name(&data[names..names + LABEL_NAME_MAX]),
+            object: read_name(&data[names + LABEL_NAME_MAX..names + 2 * LABEL_NAME_MAX]),
+            example: read_name(&data[names + 2 * LABEL_NAME_MAX..DENIAL_SIZE]),
+        })
+    }
+}
+
+impl Status {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < STATUS_SIZE {
+            return None;
+        }
+        Some(Status {
+            mode: read_u32(data, 0),
+            labels: read_u32(data, 4),
+            rules: read_u32(data, 8),
+            processes: read_u32(data, 12),
+            checks: read_u64(data, 16),
+            denials: read_u64(data, 24),
+            generation: read_u64(data, 32),
+        })
+    }
+}
+
+/// `allow` lines covering `denials`, one per subject, object and class
+pub fn learn(denials: &[Denial]) -> String {
+    let mut merged: Vec<Denial> = Vec::new();
+    for denial in denials {
+        match merged.iter_mut().find(|entry| {
+            entry.subject == denial.subject && entry.object == denial.object && entry.class == denial.class
+        }) {
+            Some(entry) => {
+                entry.perms |= denial.perms;
+                entry.count += denial.count;
+            }
+            None => merged.push(denial.clone()),
+        }
+    }
+
+    let mut out = String::new();
+    for denial in merged.iter() {
+        let perms: Vec<&str> = perm_names(denial.perms).collect();
+        out.push_str(&format!(
+            "allow {} {} {} {}  # {} denied, first {}\n",
+            denial.subject,
+            denial.object,
+            class_name(denial.class),
+            perms.join(","),
+            denial.count,
+            denial.example
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Policy, CLASS_FILE, CLASS_SOCKET, PERM_CONNECT, PERM_READ, PERM_WRITE};
+
+    fn record(subject: &str, object: &str, class: u32, perms: u32, count: u64, example: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        for field in [1, 2, class, perms] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&count.to_le_bytes());
+        for (name, size) in [(subject, LABEL_NAME_MAX), (object, LABEL_NAME_MAX), (example, PATTERN_MAX)] {
+            data.extend_from_slice(name.as_bytes());
+            data.resize(data.len() + size - name.len(), 0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_decodes_kernel_records() {
+        let data = record("httpd_t", "web_t", CLASS_FILE, PERM_READ, 3, "/srv/www/index.html");
+        assert_eq!(data.len(), DENIAL_SIZE);
+        let denial = Denial::decode(&data).unwrap();
+        assert_eq!(denial.subject, "httpd_t");
+        assert_eq!(denial.object, "web_t");
+        assert_eq!(denial.example, "/srv/www/index.html");
+        assert_eq!((denial.class, denial.perms, denial.count), (CLASS_FILE, PERM_READ, 3));
+        assert!(Denial::decode(&data[..DENIAL_SIZE - 1]).is_none());
+
+        let mut status = Vec::new();
+        for field in [2u32, 4, 7, 12] {
+            status.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [900u64, 5, 1] {
+            status.extend_from_slice(&field.to_le_bytes());
+        }
+        let status = Status::decode(&status).unwrap();
+        assert_eq!((status.mode, status.processes, status.checks, status.generation), (2, 12, 900, 1));
+    }
+
+    #[test]
+    fn test_learns_rules_the_policy_accepts() {
+        let denials = [
+            Denial::decode(&record("httpd_t", "web_t", CLASS_FILE, PERM_READ, 3, "/srv/www/a")).unwrap(),
+            Denial::decode(&record("httpd_t", "unlabeled", CLASS_SOCKET, PERM_CONNECT, 1, "tcp:5432")).unwrap(),
+            Denial::decode(&record("httpd_t", "web_t", CLASS_FILE, PERM_WRITE, 2, "/srv/www/b")).unwrap(),
+        ];
+        let learned = learn(&denials);
+        assert_eq!(
+            learned,
+            "allow httpd_t web_t file read,write  # 5 denied, first /srv/www/a\n\
+             allow httpd_t unlabeled socket connect  # 1 denied, first tcp:5432\n"
+        );
+
+        let policy = Policy::parse(&format!("label httpd_t web_t\n{}", learned)).unwrap();
+        assert_eq!(policy.rules.len(), 2);
+    }
+}
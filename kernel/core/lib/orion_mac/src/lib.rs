@@ -0,0 +1,132 @@
+/*
+ * Orion Operating System - Mandatory Access Control Policies
+ *
+ * The kernel checks requests against a compiled policy (see
+ * capabilities/mac.h); this crate compiles it from text (policy.rs) and
+ * reads back what the kernel recorded (audit.rs). A policy labels
+ * subjects and objects, then allows labels to act on labels:
+ *
+ *   # The web server reads its pages and serves port 80
+ *   label httpd_t web_t
+ *   subject program /usr/bin/httpd httpd_t
+ *   object file /srv/www* web_t
+ *   object socket tcp:80 web_t
+ *   allow httpd_t web_t file read
+ *   allow httpd_t web_t socket bind
+ *   mode enforcing
+ *
+ * Anything no rule allows is denied. In permissive mode denials are only
+ * recorded, and the recorded denials are turned back into `allow` lines
+ * (audit.rs) to grow a policy from what the system actually does.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod audit;
+pub mod policy;
+
+pub use audit::{learn, Denial, Status, DENIAL_SIZE, STATUS_SIZE};
+pub use policy::{Context, Label, Policy, Rule};
+
+// Limits of the kernel tables
+pub const MAX_LABELS: usize = 64;
+pub const MAX_SUBJECT_CONTEXTS: usize = 64;
+pub const MAX_OBJECT_CONTEXTS: usize = 128;
+pub const MAX_RULES: usize = 512;
+/// Label names, NUL included
+pub const LABEL_NAME_MAX: usize = 32;
+/// Patterns and object names, NUL included
+pub const PATTERN_MAX: usize = 96;
+
+/// Label 0 of every policy, carried by whatever nothing labels
+pub const UNLABELED: &str = "unlabeled";
+
+// Object classes
+pub const CLASS_FILE: u32 = 1;
+pub const CLASS_DEVICE: u32 = 2;
+pub const CLASS_SOCKET: u32 = 3;
+pub const CLASS_DRIVER: u32 = 4;
+pub const CLASS_PROCESS: u32 = 5;
+pub const CLASS_POLICY: u32 = 6;
+
+// Permissions
+pub const PERM_READ: u32 = 1 << 0;
+pub const PERM_WRITE: u32 = 1 << 1;
+pub const PERM_CREATE: u32 = 1 << 2;
+pub const PERM_EXECUTE: u32 = 1 << 3;
+pub const PERM_BIND: u32 = 1 << 4;
+pub const PERM_CONNECT: u32 = 1 << 5;
+pub const PERM_LOAD: u32 = 1 << 6;
+pub const PERM_RELABEL: u32 = 1 << 7;
+
+// Subject context kinds
+pub const SUBJECT_PROGRAM: u32 = 1;
+pub const SUBJECT_USER: u32 = 2;
+
+// Modes
+pub const MODE_DISABLED: u32 = 0;
+pub const MODE_PERMISSIVE: u32 = 1;
+pub const MODE_ENFORCING: u32 = 2;
+
+/// Classes by name, with the permissions that apply to each
+const CLASSES: &[(&str, u32, u32)] = &[
+    ("file", CLASS_FILE, PERM_READ | PERM_WRITE | PERM_CREATE | PERM_EXECUTE),
+    ("device", CLASS_DEVICE, PERM_READ | PERM_WRITE),
+    ("socket", CLASS_SOCKET, PERM_BIND | PERM_CONNECT),
+    ("driver", CLASS_DRIVER, PERM_LOAD),
+    ("process", CLASS_PROCESS, PERM_RELABEL),
+    ("policy", CLASS_POLICY, PERM_LOAD),
+];
+
+const PERMS: &[(&str, u32)] = &[
+    ("read", PERM_READ),
+    ("write", PERM_WRITE),
+    ("create", PERM_CREATE),
+    ("execute", PERM_EXECUTE),
+    ("bind", PERM_BIND),
+    ("connect", PERM_CONNECT),
+    ("load", PERM_LOAD),
+    ("relabel", PERM_RELABEL),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyError {
+    /// Syntax error or unknown keyword on the given 1-based line
+    Invalid(usize),
+    /// A line uses a label not declared before it
+    UnknownLabel(usize),
+    /// A permission that does not apply to the class of its rule
+    BadPermission(usize),
+    /// More labels, contexts or rules than the kernel holds
+    TooMany,
+}
+
+pub fn class_by_name(name: &str) -> Option<u32> {
+    CLASSES.iter().find(|(class, _, _)| *class == name).map(|(_, class, _)| *class)
+}
+
+pub fn class_name(class: u32) -> &'static str {
+    CLASSES.iter().find(|(_, value, _)| *value == class).map_or("unknown", |(name, _, _)| name)
+}
+
+/// Permissions that apply to `class`
+pub fn class_perms(class: u32) -> u32 {
+    CLASSES.iter().find(|(_, value, _)| *value == class).map_or(0, |(_, _, perms)| *perms)
+}
+
+pub fn perm_by_name(name: &str) -> Option<u32> {
+    PERMS.iter().find(|(perm, _)| *perm == name).map(|(_, perm)| *perm)
+}
+
+/// Names of the permissions in `perms`, in bit order
+pub fn perm_names(perms: u32) -> impl Iterator<Item = &'static str> {
+    PERMS.iter().filter(move |(_, perm)| perms & perm != 0).map(|(name, _)| *name)
+}
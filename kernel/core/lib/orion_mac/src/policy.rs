@@ -0,0 +1,319 @@
+/*
+ * Orion Operating System - MAC Policy Compiler
+ *
+ * A policy is a list of statements, one per line, `#` starting a
+ * comment:
+ *
+ *   label <name>...                          declare labels
+ *   permissive <label>...                    only record denials of these subjects
+ *   subject program <pattern> <label>        label processes by executable path
+ *   subject user <pattern> <label>           label login sessions by user name
+ *   object <class> <pattern> <label>         label objects of a class by name
+ *   allow <subject> <object> <class> <perm>[,<perm>...]
+ *   mode enforcing|permissive                permissive unless stated
+ *
+ * Labels are declared before they are used; `unlabeled` always exists.
+ * A pattern is a name, or a prefix when it ends with `*`. Where several
+ * contexts match, the most specific wins: contexts are ordered by the
+ * length of what they match literally, an exact name before a prefix of
+ * the same length, since the kernel takes the first match. Rules on the
+ * same labels and class are merged.
+ *
+ * The compiled layout is mac_policy_header_t then the tables of
+ * capabilities/mac.h, little-endian.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{
+    class_by_name, class_perms, perm_by_name, PolicyError, LABEL_NAME_MAX, MAX_LABELS, MAX_OBJECT_CONTEXTS, MAX_RULES,
+    MAX_SUBJECT_CONTEXTS, PATTERN_MAX, SUBJECT_PROGRAM, SUBJECT_USER, UNLABELED,
+};
+
+pub const POLICY_MAGIC: u32 = 0x4341_4d4f;
+pub const POLICY_VERSION: u32 = 1;
+pub const POLICY_FLAG_ENFORCING: u32 = 1 << 0;
+pub const LABEL_FLAG_PERMISSIVE: u32 = 1 << 0;
+
+// Sizes of the kernel records
+pub const HEADER_SIZE: usize = 32;
+pub const LABEL_SIZE: usize = LABEL_NAME_MAX + 4;
+pub const CONTEXT_SIZE: usize = 8 + PATTERN_MAX;
+pub const RULE_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub name: String,
+    pub permissive: bool,
+}
+
+/// A subject or object context; `kind` is the subject kind or the object class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    pub kind: u32,
+    pub pattern: String,
+    pub label: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub subject: u32,
+    pub object: u32,
+    pub class: u32,
+    pub perms: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    pub labels: Vec<Label>,
+    pub subjects: Vec<Context>,
+    pub objects: Vec<Context>,
+    pub rules: Vec<Rule>,
+    pub enforcing: bool,
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() < LABEL_NAME_MAX
+        && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.'))
+}
+
+fn valid_pattern(pattern: &str) -> bool {
+    let literal = pattern.strip_suffix('*').unwrap_or(pattern);
+    !pattern.is_empty() && pattern.len() < PATTERN_MAX && !literal.contains('*')
+}
+
+/// Order of contexts, most specific first
+fn specificity(context: &Context) -> (core::cmp::Reverse<usize>, bool) {
+    match context.pattern.strip_suffix('*') {
+        Some(prefix) => (core::cmp::Reverse(prefix.len()), true),
+        None => (core::cmp::Reverse(context.pattern.len()), false),
+    }
+}
+
+fn put_name(out: &mut Vec<u8>, name: &str, size: usize) {
+    out.extend_from_slice(name.as_bytes());
+    out.resize(out.len() + size - name.len(), 0);
+}
+
+impl Policy {
+    pub fn label(&self, name: &str) -> Option<u32> {
+        self.labels.iter().position(|label| label.name == name).map(|index| index as u32)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, PolicyError> {
+        let mut policy = Policy {
+            labels: alloc::vec![Label { name: String::from(UNLABELED), permissive: false }],
+            subjects: Vec::new(),
+            objects: Vec::new(),
+            rules: Vec::new(),
+            enforcing: false,
+        };
+
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+            let invalid = PolicyError::Invalid(index + 1);
+            let unknown = PolicyError::UnknownLabel(index + 1);
+            let Some((&keyword, args)) = words.split_first() else {
+                continue;
+            };
+
+            match (keyword, args) {
+                ("label", names) if !names.is_empty() => {
+                    for name in names {
+                        if !valid_name(name) || policy.label(name).is_some() {
+                            return Err(invalid);
+                        }
+                        policy.labels.push(Label { name: String::from(*name), permissive: false });
+                    }
+                }
+                ("permissive", names) if !names.is_empty() => {
+                    for name in names {
+                        let label = policy.label(name).ok_or(unknown)?;
+                        policy.labels[label as usize].permissive = true;
+                    }
+                }
+                ("subject", [kind, pattern, label]) => {
+                    let kind = match *kind {
+                        "program" => SUBJECT_PROGRAM,
+                        "user" => SUBJECT_USER,
+                        _ => return Err(invalid),
+                    };
+                    if !valid_pattern(pattern) {
+                        return Err(invalid);
+                    }
+                    let label = policy.label(label).ok_or(unknown)?;
+                    policy.subjects.push(Context { kind, pattern: String::from(*pattern), label });
+                }
+                ("object", [class, pattern, label]) => {
+                    let class = class_by_name(class).ok_or(invalid)?;
+                    if !valid_pattern(pattern) {
+                        return Err(invalid);
+                    }
+                    let label = policy.label(label).ok_or(unknown)?;
+                    policy.objects.push(Context { kind: class, pattern: String::from(*pattern), label });
+                }
+                ("allow", [subject, object, class, perms]) => {
+                    let class = class_by_name(class).ok_or(invalid)?;
+                    let mut mask = 0;
+                    for perm in perms.split(',').filter(|perm| !perm.is_empty()) {
+                        mask |= perm_by_name(perm).ok_or(invalid)?;
+                    }
+                    if mask == 0 || mask & !class_perms(class) != 0 {
+                        return Err(PolicyError::BadPermission(index + 1));
+                    }
+                    let subject = policy.label(subject).ok_or(unknown)?;
+                    let object = policy.label(object).ok_or(unknown)?;
+                    match policy
+                        .rules
+                        .iter_mut()
+                        .find(|rule| rule.subject == subject && rule.object == object && rule.class == class)
+                    {
+                        Some(rule) => rule.perms |= mask,
+                        None => policy.rules.push(Rule { subject, object, class, perms: mask }),
+                    }
+                }
+                ("mode", ["enforcing"]) => policy.enforcing = true,
+                ("mode", ["permissive"]) => policy.enforcing = false,
+                _ => return Err(invalid),
+            }
+        }
+
+        if policy.labels.len() > MAX_LABELS
+            || policy.subjects.len() > MAX_SUBJECT_CONTEXTS
+            || policy.objects.len() > MAX_OBJECT_CONTEXTS
+            || policy.rules.len() > MAX_RULES
+        {
+            return Err(PolicyError::TooMany);
+        }
+        policy.subjects.sort_by_key(specificity);
+        policy.objects.sort_by_key(specificity);
+        Ok(policy)
+    }
+
+    /// Encode for MAC_CTL_LOAD
+    pub fn encode(&self) -> Vec<u8> {
+        let size = HEADER_SIZE
+            + self.labels.len() * LABEL_SIZE
+            + (self.subjects.len() + self.objects.len()) * CONTEXT_SIZE
+            + self.rules.len() * RULE_SIZE;
+        let mut out = Vec::with_capacity(size);
+        let flags = if self.enforcing { POLICY_FLAG_ENFORCING } else { 0 };
+        for field in [
+            POLICY_MAGIC,
+            POLICY_VERSION,
+            flags,
+            self.labels.len() as u32,
+            self.subjects.len() as u32,
+            self.objects.len() as u32,
+            self.rules.len() as u32,
+            0,
+        ] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        for label in self.labels.iter() {
+            put_name(&mut out, &label.name, LABEL_NAME_MAX);
+            let flags = if label.permissive { LABEL_FLAG_PERMISSIVE } else { 0 };
+            out.extend_from_slice(&flags.to_le_bytes());
+        }
+        for context in self.subjects.iter().chain(self.objects.iter()) {
+            out.extend_from_slice(&context.kind.to_le_bytes());
+            out.extend_from_slice(&context.label.to_le_bytes());
+            put_name(&mut out, &context.pattern, PATTERN_MAX);
+        }
+        for rule in self.rules.iter() {
+            for field in [rule.subject, rule.object, rule.class, rule.perms] {
+                out.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CLASS_FILE, CLASS_SOCKET, PERM_BIND, PERM_READ, PERM_WRITE};
+
+    const WEB_POLICY: &str = "\
+# The web server reads its pages and serves port 80
+label httpd_t web_t logs_t
+permissive logs_t
+subject program /usr/bin/httpd httpd_t
+object file /srv/* web_t
+object file /srv/www/private unlabeled
+object file /srv/www/* web_t
+object socket tcp:80 web_t
+allow httpd_t web_t file read
+allow httpd_t web_t socket bind
+allow httpd_t web_t file write   # uploads
+mode enforcing
+";
+
+    #[test]
+    fn test_parses_policy() {
+        let policy = Policy::parse(WEB_POLICY).unwrap();
+        assert_eq!(policy.labels.len(), 4);
+        assert_eq!(policy.label("unlabeled"), Some(0));
+        assert!(policy.labels[3].permissive && !policy.labels[1].permissive);
+        assert!(policy.enforcing);
+
+        // The most specific object contexts come first
+        let patterns: Vec<&str> = policy.objects.iter().map(|context| context.pattern.as_str()).collect();
+        assert_eq!(patterns, ["/srv/www/private", "/srv/www/*", "tcp:80", "/srv/*"]);
+
+        assert_eq!(
+            policy.rules,
+            [
+                Rule { subject: 1, object: 2, class: CLASS_FILE, perms: PERM_READ | PERM_WRITE },
+                Rule { subject: 1, object: 2, class: CLASS_SOCKET, perms: PERM_BIND },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_statements() {
+        assert_eq!(Policy::parse("label a\nlabel a\n"), Err(PolicyError::Invalid(2)));
+        assert_eq!(Policy::parse("label a\nallow a b file read\n"), Err(PolicyError::UnknownLabel(2)));
+        assert_eq!(Policy::parse("label a\nallow a a socket read\n"), Err(PolicyError::BadPermission(2)));
+        assert_eq!(Policy::parse("label a\nobject pipe /x a\n"), Err(PolicyError::Invalid(2)));
+        assert_eq!(Policy::parse("label a\nobject file /x*/y a\n"), Err(PolicyError::Invalid(2)));
+        assert_eq!(Policy::parse("mode strict"), Err(PolicyError::Invalid(1)));
+
+        let mut many = String::new();
+        for index in 0..MAX_LABELS {
+            many.push_str(&alloc::format!("label l{}\n", index));
+        }
+        assert_eq!(Policy::parse(&many), Err(PolicyError::TooMany));
+    }
+
+    #[test]
+    fn test_encodes_kernel_layout() {
+        let policy = Policy::parse(WEB_POLICY).unwrap();
+        let encoded = policy.encode();
+        assert_eq!(encoded.len(), HEADER_SIZE + 4 * LABEL_SIZE + 5 * CONTEXT_SIZE + 2 * RULE_SIZE);
+        assert_eq!(encoded[..4], *b"OMAC");
+        assert_eq!(encoded[8..12], POLICY_FLAG_ENFORCING.to_le_bytes());
+        assert_eq!(encoded[12..16], 4u32.to_le_bytes());
+
+        let label = HEADER_SIZE + 3 * LABEL_SIZE;
+        assert_eq!(&encoded[label..label + 7], b"logs_t\0");
+        assert_eq!(encoded[label + LABEL_NAME_MAX..label + LABEL_SIZE], LABEL_FLAG_PERMISSIVE.to_le_bytes());
+
+        let subject = HEADER_SIZE + 4 * LABEL_SIZE;
+        assert_eq!(encoded[subject..subject + 4], SUBJECT_PROGRAM.to_le_bytes());
+        assert_eq!(encoded[subject + 4..subject + 8], 1u32.to_le_bytes());
+        assert_eq!(&encoded[subject + 8..subject + 23], b"/usr/bin/httpd\0");
+
+        let rule = encoded.len() - RULE_SIZE;
+        assert_eq!(encoded[rule + 8..rule + 12], CLASS_SOCKET.to_le_bytes());
+        assert_eq!(encoded[rule + 12..], PERM_BIND.to_le_bytes());
+    }
+}
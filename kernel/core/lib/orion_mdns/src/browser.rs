@@ -0,0 +1,229 @@
+/*
+ * Orion Operating System - mDNS Browser
+ *
+ * Cache of the records heard on the link and the service instances they
+ * describe. Browsing a service type sends a PTR query listing the
+ * instances already known (known-answer suppression); every response
+ * heard, solicited or not, feeds the cache. Records expire with their TTL;
+ * a goodbye (TTL 0) removes its record one second later and a record with
+ * the cache-flush bit replaces the older records of its name and type.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::message::{names_equal, Message, Question, Record, RecordData, TYPE_A, TYPE_PTR, TYPE_SRV, TYPE_TXT};
+use crate::responder::DOMAIN;
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// Records kept at most; the ones closest to expiry are dropped first
+pub const MAX_CACHE_RECORDS: usize = 256;
+
+/// Service instance resolved from the cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovered {
+    /// Instance label, e.g. "Orion Management"
+    pub instance: String,
+    /// Service type without the domain, e.g. "_orion-mgmt._tcp"
+    pub service_type: String,
+    /// Target host name of the SRV record, empty until resolved
+    pub host: String,
+    /// Address of the host, 0 until resolved
+    pub address: u32,
+    pub port: u16,
+    pub txt: Vec<String>,
+}
+
+struct Entry {
+    record: Record,
+    received_ns: u64,
+    expires_ns: u64,
+}
+
+impl Entry {
+    /// TTL left at `now_ns`, in seconds
+    fn remaining(&self, now_ns: u64) -> u32 {
+        (self.expires_ns.saturating_sub(now_ns) / NS_PER_SEC) as u32
+    }
+}
+
+#[derive(Default)]
+pub struct Browser {
+    cache: Vec<Entry>,
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// PTR query for a service type with the cached instances as known answers
+    pub fn query(&self, service_type: &str, now_ns: u64) -> Message {
+        let name = format!("{}.{}", service_type, DOMAIN);
+        let mut message = Message::query();
+        message.questions.push(Question { name: name.clone(), qtype: TYPE_PTR, unicast: false });
+        for entry in &self.cache {
+            if entry.record.data.rtype() == TYPE_PTR && names_equal(&entry.record.name, &name) {
+                let mut known = entry.record.clone();
+                known.ttl = entry.remaining(now_ns);
+                if known.ttl > 0 {
+                    message.answers.push(known);
+                }
+            }
+        }
+        message
+    }
+
+    fn insert_record(&mut self, record: &Record, now_ns: u64) {
+        let expires_ns = now_ns + if record.ttl == 0 { NS_PER_SEC } else { record.ttl as u64 * NS_PER_SEC };
+        if record.cache_flush {
+            // Older records of a unique name are stale once the owner sends a new set;
+            // those received in the last second belong to the same set (RFC 6762 section 10.2)
+            for entry in self.cache.iter_mut() {
+                if names_equal(&entry.record.name, &record.name)
+                    && entry.record.data.rtype() == record.data.rtype()
+                    && now_ns.saturating_sub(entry.received_ns) > NS_PER_SEC
+                {
+                    entry.expires_ns = entry.expires_ns.min(now_ns + NS_PER_SEC);
+                }
+            }
+        }
+        match self.cache.iter_mut().find(|entry| entry.record.same_as(record)) {
+            Some(entry) => {
+                entry.record.ttl = record.ttl;
+                entry.received_ns = now_ns;
+                entry.expires_ns = expires_ns;
+            }
+            None => {
+                if self.cache.len() >= MAX_CACHE_RECORDS {
+                    if let Some(index) = (0..self.cache.len()).min_by_key(|&index| self.cache[index].expires_ns) {
+                        self.cache.swap_remove(index);
+                    }
+                }
+                self.cache.push(Entry { record: record.clone(), received_ns: now_ns, expires_ns });
+            }
+        }
+    }
+
+    /// Feed a received message; queries are ignored
+    pub fn insert(&mut self, message: &Message, now_ns: u64) {
+        if !message.is_response() {
+            return;
+        }
+        for record in message.answers.iter().chain(&message.authorities).chain(&message.additionals) {
+            self.insert_record(record, now_ns);
+        }
+    }
+
+    /// Drop expired records
+    pub fn expire(&mut self, now_ns: u64) {
+        self.cache.retain(|entry| entry.expires_ns > now_ns);
+    }
+
+    fn lookup(&self, name: &str, rtype: u16) -> Option<&RecordData> {
+        self.cache
+            .iter()
+            .filter(|entry| entry.record.data.rtype() == rtype && names_equal(&entry.record.name, name))
+            .max_by_key(|entry| entry.received_ns)
+            .map(|entry| &entry.record.data)
+    }
+
+    /// Instances of a service type, resolved as far as the cache allows
+    pub fn services(&self, service_type: &str) -> Vec<Discovered> {
+        let name = format!("{}.{}", service_type, DOMAIN);
+        let mut found = Vec::new();
+        for entry in &self.cache {
+            let instance_name = match &entry.record.data {
+                RecordData::Ptr(target) if names_equal(&entry.record.name, &name) => target,
+                _ => continue,
+            };
+            let instance = match instance_name.len().checked_sub(name.len() + 1) {
+                Some(end) if names_equal(&instance_name[end + 1..], &name) => instance_name[..end].to_string(),
+                _ => continue,
+            };
+            let mut discovered = Discovered {
+                instance,
+                service_type: service_type.to_string(),
+                host: String::new(),
+                address: 0,
+                port: 0,
+                txt: Vec::new(),
+            };
+            if let Some(RecordData::Srv { port, target, .. }) = self.lookup(instance_name, TYPE_SRV) {
+                discovered.port = *port;
+                discovered.host = target.clone();
+                if let Some(RecordData::A(address)) = self.lookup(target, TYPE_A) {
+                    discovered.address = *address;
+                }
+            }
+            if let Some(RecordData::Txt(txt)) = self.lookup(instance_name, TYPE_TXT) {
+                discovered.txt = txt.clone();
+            }
+            found.push(discovered);
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responder::{Responder, Service};
+    use crate::MDNS_PORT;
+    use alloc::vec;
+
+    #[test]
+    fn test_resolves_and_expires_instances() {
+        let mut responder = Responder::new("orion", 0x0A00_0002);
+        let service = Service {
+            instance: "Orion Metrics".to_string(),
+            service_type: "_orion-metrics._tcp".to_string(),
+            port: 9100,
+            txt: vec!["path=/metrics".to_string()],
+        };
+        responder.register(service.clone());
+
+        let mut browser = Browser::new();
+        let query = browser.query("_orion-metrics._tcp", 0);
+        assert!(query.answers.is_empty());
+        let (response, _) = responder.respond(&query, MDNS_PORT).unwrap();
+        browser.insert(&Message::decode(&response.encode()).unwrap(), 0);
+
+        let found = browser.services("_orion-metrics._tcp");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].instance, "Orion Metrics");
+        assert_eq!((found[0].host.as_str(), found[0].address, found[0].port), ("orion.local", 0x0A00_0002, 9100));
+        assert_eq!(found[0].txt, service.txt);
+
+        // The next query lists the instance, so the responder stays quiet
+        let query = browser.query("_orion-metrics._tcp", 10 * NS_PER_SEC);
+        assert_eq!(query.answers.len(), 1);
+        assert!(responder.respond(&query, MDNS_PORT).is_none());
+
+        // A new address with the cache-flush bit replaces the old one
+        responder.set_host("orion", 0x0A00_0009);
+        browser.insert(&responder.announcement(), 20 * NS_PER_SEC);
+        browser.expire(22 * NS_PER_SEC);
+        assert_eq!(browser.services("_orion-metrics._tcp")[0].address, 0x0A00_0009);
+
+        browser.insert(&responder.service_goodbye(&service), 30 * NS_PER_SEC);
+        browser.expire(32 * NS_PER_SEC);
+        assert!(browser.services("_orion-metrics._tcp").is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+/*
+ * Orion Operating System - mDNS Discovery Client
+ *
+ * IPC protocol of the mdns service and a client for it. Services register
+ * the instances they want advertised (REGISTER, UNREGISTER) and tools
+ * browse for instances of a service type (BROWSE): the first request for a
+ * type starts browsing it on the link, and every request returns the
+ * instances known so far. The host name and address are set by whoever
+ * configures the interface (SET_HOST).
+ *
+ * Requests start with a u32 opcode; strings are a u16 length and UTF-8
+ * bytes, everything little-endian. Replies are an i32 status followed by
+ * the payload of the opcode.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::browser::Discovered;
+
+/// Service name of the responder on the IPC bus
+pub const MDNS_SERVICE_NAME: &str = "mdns";
+
+pub const MDNS_OP_REGISTER: u32 = 1;
+pub const MDNS_OP_UNREGISTER: u32 = 2;
+pub const MDNS_OP_BROWSE: u32 = 3;
+pub const MDNS_OP_SET_HOST: u32 = 4;
+
+pub const MDNS_OK: i32 = 0;
+pub const MDNS_EINVAL: i32 = -22;
+pub const MDNS_EPERM: i32 = -1;
+pub const MDNS_ENOENT: i32 = -2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MdnsRequest {
+    /// Advertise an instance (admin)
+    Register { instance: String, service_type: String, port: u16, txt: Vec<String> },
+    /// Withdraw an instance, sending goodbyes (admin)
+    Unregister { instance: String, service_type: String },
+    /// Instances of a service type, browsing it from now on
+    Browse { service_type: String },
+    /// Host name label and IPv4 address advertised in A records (admin)
+    SetHost { name: String, address: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientError {
+    /// The service did not answer
+    Unreachable,
+    /// Malformed reply
+    BadReply,
+    /// Negative status from the service
+    Status(i32),
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Little-endian reader over a request or reply
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < count {
+            return None;
+        }
+        let (head, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Some(head)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.u16()? as usize;
+        core::str::from_utf8(self.take(length)?).ok().map(String::from)
+    }
+
+    fn strings(&mut self) -> Option<Vec<String>> {
+        let count = self.u16()?;
+        (0..count).map(|_| self.string()).collect()
+    }
+}
+
+fn put_strings(out: &mut Vec<u8>, values: &[String]) {
+    out.extend_from_slice(&(values.len() as u16).to_le_bytes());
+    for value in values {
+        put_str(out, value);
+    }
+}
+
+impl MdnsRequest {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            MdnsRequest::Register { instance, service_type, port, txt } => {
+                out.extend_from_slice(&MDNS_OP_REGISTER.to_le_bytes());
+                out.extend_from_slice(&port.to_le_bytes());
+                put_str(&mut out, instance);
+                put_str(&mut out, service_type);
+                put_strings(&mut out, txt);
+            }
+            MdnsRequest::Unregister { instance, service_type } => {
+                out.extend_from_slice(&MDNS_OP_UNREGISTER.to_le_bytes());
+                put_str(&mut out, instance);
+                put_str(&mut out, service_type);
+            }
+            MdnsRequest::Browse { service_type } => {
+                out.extend_from_slice(&MDNS_OP_BROWSE.to_le_bytes());
+                put_str(&mut out, service_type);
+            }
+            MdnsRequest::SetHost { name, address } => {
+                out.extend_from_slice(&MDNS_OP_SET_HOST.to_le_bytes());
+                out.extend_from_slice(&address.to_le_bytes());
+                put_str(&mut out, name);
+            }
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader { bytes };
+        let request = match reader.u32()? {
+            MDNS_OP_REGISTER => {
+                let port = reader.u16()?;
+                MdnsRequest::Register {
+                    port,
+                    instance: reader.string()?,
+                    service_type: reader.string()?,
+                    txt: reader.strings()?,
+                }
+            }
+            MDNS_OP_UNREGISTER => {
+                MdnsRequest::Unregister { instance: reader.string()?, service_type: reader.string()? }
+            }
+            MDNS_OP_BROWSE => MdnsRequest::Browse { service_type: reader.string()? },
+            MDNS_OP_SET_HOST => {
+                let address = reader.u32()?;
+                MdnsRequest::SetHost { address, name: reader.string()? }
+            }
+            _ => return None,
+        };
+        if !reader.bytes.is_empty() {
+            return None;
+        }
+        Some(request)
+    }
+}
+
+/// BROWSE reply: MDNS_OK, count u32, then each instance
+pub fn encode_discovered(found: &[Discovered]) -> Vec<u8> {
+    let mut out = MDNS_OK.to_le_bytes().to_vec();
+    out.extend_from_slice(&(found.len() as u32).to_le_bytes());
+    for discovered in found {
+        put_str(&mut out, &discovered.instance);
+        put_str(&mut out, &discovered.service_type);
+        put_str(&mut out, &discovered.host);
+        out.extend_from_slice(&discovered.address.to_le_bytes());
+        out.extend_from_slice(&discovered.port.to_le_bytes());
+        put_strings(&mut out, &discovered.txt);
+    }
+    out
+}
+
+pub fn decode_discovered(payload: &[u8]) -> Option<Vec<Discovered>> {
+    let mut reader = Reader { bytes: payload };
+    let count = reader.u32()?;
+    let mut found = Vec::new();
+    for _ in 0..count {
+        found.push(Discovered {
+            instance: reader.string()?,
+            service_type: reader.string()?,
+            host: reader.string()?,
+            address: reader.u32()?,
+            port: reader.u16()?,
+            txt: reader.strings()?,
+        });
+    }
+    Some(found)
+}
+
+/// Request/reply transport to the mdns service
+pub trait MdnsChannel {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Client of the mdns service
+pub struct Discovery<C: MdnsChannel> {
+    channel: C,
+}
+
+impl<C: MdnsChannel> Discovery<C> {
+    pub fn new(channel: C) -> Self {
+        Self { channel }
+    }
+
+    /// Send a request, returning the payload after a non-negative status
+    fn call(&mut self, request: &MdnsRequest) -> Result<Vec<u8>, ClientError> {
+        let reply = self.channel.call(&request.encode()).ok_or(ClientError::Unreachable)?;
+        let status = reply.get(0..4).ok_or(ClientError::BadReply)?;
+        let status = i32::from_le_bytes([status[0], status[1], status[2], status[3]]);
+        if status < 0 {
+            return Err(ClientError::Status(status));
+        }
+        Ok(reply[4..].to_vec())
+    }
+
+    /// Instances of `service_type` (e.g. "_nbd._tcp") known to the responder
+    pub fn browse(&mut self, service_type: &str) -> Result<Vec<Discovered>, ClientError> {
+        let payload = self.call(&MdnsRequest::Browse { service_type: String::from(service_type) })?;
+        decode_discovered(&payload).ok_or(ClientError::BadReply)
+    }
+
+    pub fn register(&mut self, instance: &str, service_type: &str, port: u16, txt: &[&str]) -> Result<(), ClientError> {
+        self.call(&MdnsRequest::Register {
+            instance: String::from(instance),
+            service_type: String::from(service_type),
+            port,
+            txt: txt.iter().map(|entry| String::from(*entry)).collect(),
+        })
+        .map(|_| ())
+    }
+
+    pub fn unregister(&mut self, instance: &str, service_type: &str) -> Result<(), ClientError> {
+        self.call(&MdnsRequest::Unregister {
+            instance: String::from(instance),
+            service_type: String::from(service_type),
+        })
+        .map(|_| ())
+    }
+
+    pub fn set_host(&mut self, name: &str, address: u32) -> Result<(), ClientError> {
+        self.call(&MdnsRequest::SetHost { name: String::from(name), address }).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Answers BROWSE with one instance and everything else with MDNS_OK
+    struct Loopback {
+        requests: Vec<MdnsRequest>,
+    }
+
+    impl MdnsChannel for Loopback {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            let request = MdnsRequest::decode(request)?;
+            self.requests.push(request.clone());
+            Some(match request {
+                MdnsRequest::Browse { service_type } => encode_discovered(&[Discovered {
+                    instance: String::from("disk0"),
+                    service_type,
+                    host: String::from("orion.local"),
+                    address: 0x0A00_0002,
+                    port: 10809,
+                    txt: vec![],
+                }]),
+                MdnsRequest::Unregister { .. } => MDNS_ENOENT.to_le_bytes().to_vec(),
+                _ => MDNS_OK.to_le_bytes().to_vec(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_talks_to_the_service() {
+        let mut discovery = Discovery::new(Loopback { requests: Vec::new() });
+        discovery.register("disk0", "_nbd._tcp", 10809, &["export=disk0"]).unwrap();
+        discovery.set_host("orion", 0x0A00_0002).unwrap();
+        let found = discovery.browse("_nbd._tcp").unwrap();
+        assert_eq!((found[0].instance.as_str(), found[0].port), ("disk0", 10809));
+        assert_eq!(discovery.unregister("disk1", "_nbd._tcp"), Err(ClientError::Status(MDNS_ENOENT)));
+
+        let requests = &discovery.channel.requests;
+        assert_eq!(requests.len(), 4);
+        assert_eq!(
+            requests[0],
+            MdnsRequest::Register {
+                instance: String::from("disk0"),
+                service_type: String::from("_nbd._tcp"),
+                port: 10809,
+                txt: vec![String::from("export=disk0")],
+            }
+        );
+        assert_eq!(MdnsRequest::decode(&[9, 0, 0, 0]), None);
+    }
+}
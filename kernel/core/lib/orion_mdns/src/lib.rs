@@ -0,0 +1,35 @@
+/*
+ * Orion Operating System - Multicast DNS and DNS-SD
+ *
+ * Zero-configuration discovery of Orion services on the local link: DNS
+ * messages as multicast DNS uses them, a responder advertising the host
+ * and its services with DNS-SD records, a browser caching what other
+ * hosts advertise, and the client of the mdns service through which
+ * services register and tools discover.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod browser;
+pub mod client;
+pub mod message;
+pub mod responder;
+
+pub use browser::{Browser, Discovered};
+pub use client::{ClientError, Discovery, MdnsChannel, MdnsRequest};
+pub use message::{DecodeError, Message, Question, Record, RecordData};
+pub use responder::{Destination, Owner, Responder, Service};
+
+/// mDNS UDP port
+pub const MDNS_PORT: u16 = 5353;
+
+/// mDNS IPv4 group, 224.0.0.251
+pub const MDNS_GROUP: u32 = 0xE000_00FB;
@@ -0,0 +1,416 @@
+/*
+ * Orion Operating System - DNS Message Encoding
+ *
+ * DNS messages as exchanged over multicast DNS (RFC 6762): header,
+ * questions and the A, PTR, TXT and SRV records DNS-SD uses, other types
+ * being carried opaquely. Names are dotted strings without the trailing
+ * dot and compare without regard to ASCII case; labels holding a dot are
+ * not supported. Names are compressed when encoding, and compression
+ * pointers are followed with a bound when decoding.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Record types
+pub const TYPE_A: u16 = 1;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_SRV: u16 = 33;
+pub const TYPE_ANY: u16 = 255;
+
+pub const CLASS_IN: u16 = 1;
+
+/// Top bit of the class: cache-flush in records, unicast-response in questions
+const CLASS_TOP_BIT: u16 = 0x8000;
+
+// Header flags
+pub const FLAG_RESPONSE: u16 = 0x8000;
+pub const FLAG_AUTHORITATIVE: u16 = 0x0400;
+
+const MAX_NAME_LENGTH: usize = 255;
+const MAX_LABEL_LENGTH: usize = 63;
+const MAX_POINTERS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    Truncated,
+    BadName,
+    BadRecord,
+}
+
+/// Whether two names are the same, ignoring ASCII case
+pub fn names_equal(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(u32),
+    Ptr(String),
+    Txt(Vec<String>),
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    Other { rtype: u16, data: Vec<u8> },
+}
+
+impl RecordData {
+    pub fn rtype(&self) -> u16 {
+        match self {
+            RecordData::A(_) => TYPE_A,
+            RecordData::Ptr(_) => TYPE_PTR,
+            RecordData::Txt(_) => TYPE_TXT,
+            RecordData::Srv { .. } => TYPE_SRV,
+            RecordData::Other { rtype, .. } => *rtype,
+        }
+    }
+
+    /// Same data, with names compared without regard to case
+    pub fn same_as(&self, other: &RecordData) -> bool {
+        match (self, other) {
+            (RecordData::Ptr(a), RecordData::Ptr(b)) => names_equal(a, b),
+            (
+                RecordData::Srv { priority, weight, port, target },
+                RecordData::Srv { priority: p, weight: w, port: o, target: t },
+            ) => priority == p && weight == w && port == o && names_equal(target, t),
+            _ => self == other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+    /// The asker accepts a unicast answer (QU question)
+    pub unicast: bool,
+}
+
+impl Question {
+    /// Whether a record answers the question
+    pub fn matches(&self, record: &Record) -> bool {
+        (self.qtype == TYPE_ANY || self.qtype == record.data.rtype()) && names_equal(&self.name, &record.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub name: String,
+    pub ttl: u32,
+    /// The record is unique to its owner: caches drop older ones of the same name and type
+    pub cache_flush: bool,
+    pub data: RecordData,
+}
+
+impl Record {
+    pub fn new(name: String, ttl: u32, cache_flush: bool, data: RecordData) -> Self {
+        Self { name, ttl, cache_flush, data }
+    }
+
+    /// Same name, type and data; TTLs may differ
+    pub fn same_as(&self, other: &Record) -> bool {
+        names_equal(&self.name, &other.name) && self.data.same_as(&other.data)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    pub id: u16,
+    pub flags: u16,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+    pub additionals: Vec<Record>,
+}
+
+impl Message {
+    pub fn query() -> Self {
+        Self::default()
+    }
+
+    pub fn response() -> Self {
+        Self { flags: FLAG_RESPONSE | FLAG_AUTHORITATIVE, ..Self::default() }
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags & FLAG_RESPONSE != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.questions.is_empty() && self.answers.is_empty() && self.authorities.is_empty()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer { out: Vec::with_capacity(512), names: Vec::new() };
+        writer.u16(self.id);
+        writer.u16(self.flags);
+        writer.u16(self.questions.len() as u16);
+        writer.u16(self.answers.len() as u16);
+        writer.u16(self.authorities.len() as u16);
+        writer.u16(self.additionals.len() as u16);
+        for question in &self.questions {
+            writer.name(&question.name);
+            writer.u16(question.qtype);
+            writer.u16(CLASS_IN | if question.unicast { CLASS_TOP_BIT } else { 0 });
+        }
+        for record in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            writer.record(record);
+        }
+        writer.out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let counts = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+
+        let mut message = Message { id, flags, ..Message::default() };
+        for _ in 0..counts[0] {
+            let name = reader.name()?;
+            let qtype = reader.u16()?;
+            let class = reader.u16()?;
+            if class & !CLASS_TOP_BIT == CLASS_IN {
+                message.questions.push(Question { name, qtype, unicast: class & CLASS_TOP_BIT != 0 });
+            }
+        }
+        for (section, count) in counts[1..].iter().enumerate() {
+            for _ in 0..*count {
+                let record = match reader.record()? {
+                    Some(record) => record,
+                    None => continue,
+                };
+                match section {
+                    0 => message.answers.push(record),
+                    1 => message.authorities.push(record),
+                    _ => message.additionals.push(record),
+                }
+            }
+        }
+        Ok(message)
+    }
+}
+
+struct Writer {
+    out: Vec<u8>,
+    /// Names already written and their offset, for compression
+    names: Vec<(String, u16)>,
+}
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn name(&mut self, name: &str) {
+        let labels: Vec<&str> = name.split('.').filter(|label| !label.is_empty()).collect();
+        for i in 0..labels.len() {
+            let suffix = labels[i..].join(".");
+            if let Some(&(_, offset)) = self.names.iter().find(|(known, _)| names_equal(known, &suffix)) {
+                self.u16(0xC000 | offset);
+                return;
+            }
+            if self.out.len() < 0x3FFF {
+                self.names.push((suffix, self.out.len() as u16));
+            }
+            let label = &labels[i].as_bytes()[..labels[i].len().min(MAX_LABEL_LENGTH)];
+            self.out.push(label.len() as u8);
+            self.out.extend_from_slice(label);
+        }
+        self.out.push(0);
+    }
+
+    fn record(&mut self, record: &Record) {
+        self.name(&record.name);
+        self.u16(record.data.rtype());
+        self.u16(CLASS_IN | if record.cache_flush { CLASS_TOP_BIT } else { 0 });
+        self.out.extend_from_slice(&record.ttl.to_be_bytes());
+
+        // Length patched once the data is written
+        let length_at = self.out.len();
+        self.u16(0);
+        match &record.data {
+            RecordData::A(address) => self.out.extend_from_slice(&address.to_be_bytes()),
+            RecordData::Ptr(target) => self.name(target),
+            RecordData::Txt(strings) => {
+                for string in strings {
+                    let bytes = &string.as_bytes()[..string.len().min(255)];
+                    self.out.push(bytes.len() as u8);
+                    self.out.extend_from_slice(bytes);
+                }
+                // An empty TXT record still holds one empty string
+                if strings.is_empty() {
+                    self.out.push(0);
+                }
+            }
+            RecordData::Srv { priority, weight, port, target } => {
+                self.u16(*priority);
+                self.u16(*weight);
+                self.u16(*port);
+                self.name(target);
+            }
+            RecordData::Other { data, .. } => self.out.extend_from_slice(data),
+        }
+        let length = (self.out.len() - length_at - 2) as u16;
+        self.out[length_at..length_at + 2].copy_from_slice(&length.to_be_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], DecodeError> {
+        let end = self.offset.checked_add(len).ok_or(DecodeError::Truncated)?;
+        let bytes = self.bytes.get(self.offset..end).ok_or(DecodeError::Truncated)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Name at the current offset, following compression pointers
+    fn name(&mut self) -> Result<String, DecodeError> {
+        let mut name = String::new();
+        let mut position = self.offset;
+        let mut resume = None;
+        let mut pointers = 0;
+        loop {
+            let length = *self.bytes.get(position).ok_or(DecodeError::Truncated)? as usize;
+            match length & 0xC0 {
+                0x00 if length == 0 => {
+                    position += 1;
+                    break;
+                }
+                0x00 => {
+                    let label = self.bytes.get(position + 1..position + 1 + length).ok_or(DecodeError::Truncated)?;
+                    if !name.is_empty() {
+                        name.push('.');
+                    }
+                    name.push_str(core::str::from_utf8(label).map_err(|_| DecodeError::BadName)?);
+                    if name.len() > MAX_NAME_LENGTH {
+                        return Err(DecodeError::BadName);
+                    }
+                    position += 1 + length;
+                }
+                0xC0 => {
+                    let low = *self.bytes.get(position + 1).ok_or(DecodeError::Truncated)? as usize;
+                    pointers += 1;
+                    if pointers > MAX_POINTERS {
+                        return Err(DecodeError::BadName);
+                    }
+                    resume.get_or_insert(position + 2);
+                    position = ((length & 0x3F) << 8) | low;
+                }
+                _ => return Err(DecodeError::BadName),
+            }
+        }
+        self.offset = resume.unwrap_or(position);
+        Ok(name)
+    }
+
+    /// Next record, None for a class other than IN
+    fn record(&mut self) -> Result<Option<Record>, DecodeError> {
+        let name = self.name()?;
+        let rtype = self.u16()?;
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let length = self.u16()? as usize;
+        let end = self.offset + length;
+        if end > self.bytes.len() {
+            return Err(DecodeError::Truncated);
+        }
+
+        let data = match rtype {
+            TYPE_A if length == 4 => RecordData::A(self.u32()?),
+            TYPE_PTR => RecordData::Ptr(self.name()?),
+            TYPE_SRV => {
+                let priority = self.u16()?;
+                let weight = self.u16()?;
+                let port = self.u16()?;
+                RecordData::Srv { priority, weight, port, target: self.name()? }
+            }
+            TYPE_TXT => {
+                let mut strings = Vec::new();
+                while self.offset < end {
+                    let len = self.take(1)?[0] as usize;
+                    let string = self.take(len)?;
+                    if !string.is_empty() {
+                        strings.push(String::from_utf8_lossy(string).into_owned());
+                    }
+                }
+                RecordData::Txt(strings)
+            }
+            TYPE_A => return Err(DecodeError::BadRecord),
+            _ => RecordData::Other { rtype, data: self.take(length)?.to_vec() },
+        };
+        if self.offset != end {
+            return Err(DecodeError::BadRecord);
+        }
+
+        if class & !CLASS_TOP_BIT != CLASS_IN {
+            return Ok(None);
+        }
+        Ok(Some(Record { name, ttl, cache_flush: class & CLASS_TOP_BIT != 0, data }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_round_trips_with_compression() {
+        let mut message = Message::response();
+        message.answers.push(Record::new(
+            "_http._tcp.local".to_string(),
+            4500,
+            false,
+            RecordData::Ptr("Orion._http._tcp.local".to_string()),
+        ));
+        message.additionals.push(Record::new(
+            "Orion._http._tcp.local".to_string(),
+            120,
+            true,
+            RecordData::Srv { priority: 0, weight: 0, port: 8443, target: "orion.local".to_string() },
+        ));
+        message.additionals.push(Record::new(
+            "Orion._http._tcp.local".to_string(),
+            4500,
+            true,
+            RecordData::Txt(vec!["path=/api".to_string()]),
+        ));
+        message.additionals.push(Record::new("orion.local".to_string(), 120, true, RecordData::A(0x0A00_0002)));
+
+        let bytes = message.encode();
+        // "local" is written once, every later occurrence is a pointer
+        assert_eq!(bytes.windows(6).filter(|window| window == b"\x05local").count(), 1);
+        assert_eq!(Message::decode(&bytes), Ok(message));
+    }
+
+    #[test]
+    fn test_rejects_pointer_loops() {
+        let mut bytes = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        // Question name pointing at itself
+        bytes.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1]);
+        assert_eq!(Message::decode(&bytes), Err(DecodeError::BadName));
+        assert_eq!(Message::decode(&bytes[..10]), Err(DecodeError::Truncated));
+    }
+}
@@ -0,0 +1,386 @@
+/*
+ * Orion Operating System - mDNS Responder
+ *
+ * Records of the local host and of the services it advertises, and the
+ * answers to queries for them (RFC 6762, DNS-SD per RFC 6763). Each
+ * service instance owns an SRV and a TXT record and is pointed to by a
+ * PTR record of its service type, itself listed under the
+ * `_services._dns-sd._udp.local` enumeration name; the host owns its A
+ * record. Answers to PTR questions carry the SRV, TXT and A records in the
+ * additional section so that browsers resolve an instance in one round.
+ *
+ * Answers already known to the asker with at least half their TTL left
+ * are left out (known-answer suppression). A response claiming one of our
+ * unique names with other data is a conflict; the owner is renamed
+ * ("orion-2", "Name (2)") and must be probed again.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::message::{names_equal, Message, Question, Record, RecordData, TYPE_ANY};
+
+pub const DOMAIN: &str = "local";
+
+/// Service type enumeration name (RFC 6763 section 9)
+pub const SERVICES_NAME: &str = "_services._dns-sd._udp.local";
+
+/// TTL of records naming the host (A, SRV), as recommended by RFC 6762
+pub const HOST_TTL: u32 = 120;
+/// TTL of the other records
+pub const SERVICE_TTL: u32 = 4500;
+/// Largest TTL in answers to legacy unicast queries
+const LEGACY_TTL: u32 = 10;
+
+/// Advertised service instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    /// Instance label, e.g. "Orion Management"
+    pub instance: String,
+    /// Service type without the domain, e.g. "_orion-mgmt._tcp"
+    pub service_type: String,
+    pub port: u16,
+    /// key=value strings of the TXT record
+    pub txt: Vec<String>,
+}
+
+impl Service {
+    pub fn type_name(&self) -> String {
+        format!("{}.{}", self.service_type, DOMAIN)
+    }
+
+    pub fn instance_name(&self) -> String {
+        format!("{}.{}.{}", self.instance, self.service_type, DOMAIN)
+    }
+}
+
+/// Owner of a name the responder must keep unique
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    Host,
+    Service(usize),
+}
+
+/// Where an answer goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Multicast,
+    /// Back to the asker only: QU questions and legacy resolvers
+    Unicast,
+}
+
+pub struct Responder {
+    host: String,
+    address: u32,
+    services: Vec<Service>,
+    pub conflicts: u64,
+}
+
+/// Label with a rename suffix bumped: "orion" -> "orion-2" -> "orion-3"
+fn bump(label: &str, separator: &str, close: &str) -> String {
+    let base = label.strip_suffix(close).unwrap_or(label);
+    if let Some((stem, number)) = base.rsplit_once(separator) {
+        if let Ok(number) = number.parse::<u32>() {
+            return format!("{}{}{}{}", stem, separator, number + 1, close);
+        }
+    }
+    format!("{}{}2{}", label, separator, close)
+}
+
+impl Responder {
+    /// Responder for `host`.local at `address` (0 while unknown: no A record)
+    pub fn new(host: &str, address: u32) -> Self {
+        Self { host: host.to_string(), address, services: Vec::new(), conflicts: 0 }
+    }
+
+    pub fn host_name(&self) -> String {
+        format!("{}.{}", self.host, DOMAIN)
+    }
+
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    pub fn set_host(&mut self, host: &str, address: u32) {
+        self.host = host.to_string();
+        self.address = address;
+    }
+
+    pub fn services(&self) -> &[Service] {
+        &self.services
+    }
+
+    /// Add or replace (same instance and type) a service
+    pub fn register(&mut self, service: Service) -> usize {
+        let name = service.instance_name();
+        match self.services.iter().position(|known| names_equal(&known.instance_name(), &name)) {
+            Some(index) => {
+                self.services[index] = service;
+                index
+            }
+            None => {
+                self.services.push(service);
+                self.services.len() - 1
+            }
+        }
+    }
+
+    pub fn unregister(&mut self, instance: &str, service_type: &str) -> Option<Service> {
+        let index = self.services.iter().position(|service| {
+            names_equal(&service.instance, instance) && names_equal(&service.service_type, service_type)
+        })?;
+        Some(self.services.remove(index))
+    }
+
+    fn host_records(&self, ttl: u32) -> Vec<Record> {
+        let mut records = Vec::new();
+        if self.address != 0 {
+            records.push(Record::new(self.host_name(), ttl.min(HOST_TTL), true, RecordData::A(self.address)));
+        }
+        records
+    }
+
+    /// PTR from the type, SRV, TXT and the enumeration PTR of one service
+    fn service_records(&self, service: &Service, ttl: u32) -> [Record; 4] {
+        let instance = service.instance_name();
+        [
+            Record::new(service.type_name(), ttl, false, RecordData::Ptr(instance.clone())),
+            Record::new(
+                instance.clone(),
+                ttl.min(HOST_TTL),
+                true,
+                RecordData::Srv { priority: 0, weight: 0, port: service.port, target: self.host_name() },
+            ),
+            Record::new(instance, ttl, true, RecordData::Txt(service.txt.clone())),
+            Record::new(SERVICES_NAME.to_string(), ttl, false, RecordData::Ptr(service.type_name())),
+        ]
+    }
+
+    /// Every record of the responder, with TTLs capped at `ttl`
+    pub fn records(&self, ttl: u32) -> Vec<Record> {
+        let mut records = self.host_records(ttl);
+        for service in &self.services {
+            for record in self.service_records(service, ttl) {
+                if !records.iter().any(|known| known.same_as(&record)) {
+                    records.push(record);
+                }
+            }
+        }
+        records
+    }
+
+    /// Unsolicited response announcing every record, sent after probing
+    pub fn announcement(&self) -> Message {
+        let mut message = Message::response();
+        message.answers = self.records(SERVICE_TTL);
+        message
+    }
+
+    /// Announcement with TTL 0, withdrawing every record before going away
+    pub fn goodbye(&self) -> Message {
+        let mut message = Message::response();
+        message.answers = self.records(0);
+        message
+    }
+
+    /// Goodbye for a single service being unregistered
+    pub fn service_goodbye(&self, service: &Service) -> Message {
+        let mut message = Message::response();
+        message.answers = self.service_records(service, 0)[..3].to_vec();
+        message
+    }
+
+    /// Probe for the unique names: ANY questions, proposed records in the authority section
+    pub fn probe(&self) -> Message {
+        let mut message = Message::query();
+        if self.address != 0 {
+            message.questions.push(Question { name: self.host_name(), qtype: TYPE_ANY, unicast: true });
+        }
+        for service in &self.services {
+            message.questions.push(Question { name: service.instance_name(), qtype: TYPE_ANY, unicast: true });
+        }
+        message.authorities = self.records(SERVICE_TTL).into_iter().filter(|record| record.cache_flush).collect();
+        message
+    }
+
+    /// Owners of the unique names another host claims with other data in a response
+    pub fn conflicts(&self, message: &Message) -> Vec<Owner> {
+        let mut owners = Vec::new();
+        if !message.is_response() {
+            return owners;
+        }
+        let ours = self.records(SERVICE_TTL);
+        for record in message.answers.iter().chain(&message.additionals) {
+            let claimed = ours.iter().any(|own| {
+                own.cache_flush
+                    && names_equal(&own.name, &record.name)
+                    && own.data.rtype() == record.data.rtype()
+                    && !own.data.same_as(&record.data)
+            });
+            if !claimed {
+                continue;
+            }
+            let owner = if names_equal(&record.name, &self.host_name()) {
+                Some(Owner::Host)
+            } else {
+                self.services
+                    .iter()
+                    .position(|service| names_equal(&service.instance_name(), &record.name))
+                    .map(Owner::Service)
+            };
+            if let Some(owner) = owner.filter(|owner| !owners.contains(owner)) {
+                owners.push(owner);
+            }
+        }
+        owners
+    }
+
+    /// Pick a new name for an owner that lost a conflict
+    pub fn rename(&mut self, owner: Owner) {
+        self.conflicts += 1;
+        match owner {
+            Owner::Host => self.host = bump(&self.host, "-", ""),
+            Owner::Service(index) => {
+                if let Some(service) = self.services.get_mut(index) {
+                    service.instance = bump(&service.instance, " (", ")");
+                }
+            }
+        }
+    }
+
+    /// Answer a query received from `source_port`, if we own any of what it asks
+    pub fn respond(&self, query: &Message, source_port: u16) -> Option<(Message, Destination)> {
+        if query.is_response() {
+            return None;
+        }
+        let legacy = source_port != crate::MDNS_PORT;
+        let ttl = if legacy { LEGACY_TTL } else { SERVICE_TTL };
+        let records = self.records(ttl);
+
+        let mut response = Message::response();
+        for question in &query.questions {
+            for record in records.iter().filter(|record| question.matches(record)) {
+                let known = query.answers.iter().any(|answer| answer.same_as(record) && answer.ttl >= record.ttl / 2);
+                if !known && !response.answers.iter().any(|answer| answer.same_as(record)) {
+                    response.answers.push(record.clone());
+                }
+            }
+        }
+        if response.answers.is_empty() {
+            return None;
+        }
+
+        // What a browser needs next: SRV and TXT of the instances, A of the targets
+        let mut wanted: Vec<(String, bool)> = Vec::new();
+        for answer in &response.answers {
+            match &answer.data {
+                RecordData::Ptr(target) => wanted.push((target.clone(), true)),
+                RecordData::Srv { target, .. } => wanted.push((target.clone(), false)),
+                _ => {}
+            }
+        }
+        for (name, instance) in wanted {
+            for record in records.iter().filter(|record| names_equal(&record.name, &name)) {
+                if !response.answers.iter().chain(&response.additionals).any(|known| known.same_as(record)) {
+                    response.additionals.push(record.clone());
+                }
+                if let (true, RecordData::Srv { target, .. }) = (instance, &record.data) {
+                    for host in records.iter().filter(|host| names_equal(&host.name, target)) {
+                        if !response.answers.iter().chain(&response.additionals).any(|known| known.same_as(host)) {
+                            response.additionals.push(host.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        if legacy {
+            // Legacy resolvers match the answer to their query id and question
+            response.id = query.id;
+            response.questions = query.questions.clone();
+            for record in response.answers.iter_mut().chain(response.additionals.iter_mut()) {
+                record.cache_flush = false;
+            }
+            return Some((response, Destination::Unicast));
+        }
+        let unicast = query.questions.iter().all(|question| question.unicast);
+        Some((response, if unicast { Destination::Unicast } else { Destination::Multicast }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{TYPE_A, TYPE_PTR, TYPE_SRV, TYPE_TXT};
+    use crate::MDNS_PORT;
+    use alloc::vec;
+
+    fn responder() -> Responder {
+        let mut responder = Responder::new("orion", 0x0A00_0002);
+        responder.register(Service {
+            instance: "Orion Management".to_string(),
+            service_type: "_orion-mgmt._tcp".to_string(),
+            port: 8443,
+            txt: vec!["path=/api".to_string()],
+        });
+        responder
+    }
+
+    fn query(name: &str, qtype: u16) -> Message {
+        let mut query = Message::query();
+        query.questions.push(Question { name: name.to_string(), qtype, unicast: false });
+        query
+    }
+
+    #[test]
+    fn test_answers_browsing_with_additionals() {
+        let responder = responder();
+        let (response, destination) = responder.respond(&query("_orion-mgmt._tcp.local", TYPE_PTR), MDNS_PORT).unwrap();
+        assert_eq!(destination, Destination::Multicast);
+        assert_eq!(response.answers.len(), 1);
+        let types: Vec<u16> = response.additionals.iter().map(|record| record.data.rtype()).collect();
+        assert_eq!(types, vec![TYPE_SRV, TYPE_A, TYPE_TXT]);
+
+        // Service type enumeration
+        let (response, _) = responder.respond(&query(SERVICES_NAME, TYPE_PTR), MDNS_PORT).unwrap();
+        assert_eq!(response.answers[0].data, RecordData::Ptr("_orion-mgmt._tcp.local".to_string()));
+
+        // Known answer with enough TTL left is not repeated
+        let mut known = query("ORION.local", TYPE_A);
+        known.answers.push(Record::new("orion.local".to_string(), HOST_TTL, true, RecordData::A(0x0A00_0002)));
+        assert!(responder.respond(&known, MDNS_PORT).is_none());
+
+        // Legacy unicast: id and question echoed, short TTL
+        let mut legacy = query("orion.local", TYPE_A);
+        legacy.id = 0x1234;
+        let (response, destination) = responder.respond(&legacy, 40000).unwrap();
+        assert_eq!((destination, response.id, response.answers[0].ttl), (Destination::Unicast, 0x1234, LEGACY_TTL));
+        assert!(responder.respond(&query("other.local", TYPE_A), MDNS_PORT).is_none());
+    }
+
+    #[test]
+    fn test_renames_on_conflict() {
+        let mut responder = responder();
+        let mut claim = Message::response();
+        claim.answers.push(Record::new("orion.local".to_string(), HOST_TTL, true, RecordData::A(0x0A00_0003)));
+        // Our own records are no conflict
+        assert!(responder.conflicts(&responder.announcement()).is_empty());
+        assert_eq!(responder.conflicts(&claim), vec![Owner::Host]);
+
+        responder.rename(Owner::Host);
+        assert_eq!(responder.host_name(), "orion-2.local");
+        responder.rename(Owner::Host);
+        assert_eq!(responder.host_name(), "orion-3.local");
+        responder.rename(Owner::Service(0));
+        responder.rename(Owner::Service(0));
+        assert_eq!(responder.services()[0].instance, "Orion Management (3)");
+        assert_eq!(responder.conflicts, 4);
+    }
+}
@@ -0,0 +1,22 @@
+/*
+ * Orion Operating System - Network Statistics
+ *
+ * Interface statistics shared by the NIC drivers and the network driver
+ * manager: aggregate counters with per-queue, per-error-cause and packet
+ * size breakdowns, and a delta-snapshot API so monitoring tools can show
+ * rates rather than lifetime totals.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+pub mod rates;
+pub mod stats;
+
+pub use rates::{NetworkRates, StatsDelta, StatsSampler};
+pub use stats::{ErrorCounts, ErrorKind, NetworkStats, QueueStats, SizeHistogram, MAX_QUEUES, SIZE_BUCKETS};
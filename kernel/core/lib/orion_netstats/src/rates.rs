@@ -0,0 +1,120 @@
+/*
+ * Orion Operating System - Network Statistics Deltas
+ *
+ * Monitoring tools want rates, not lifetime totals. StatsSampler keeps the
+ * previous snapshot of a set of counters with its timestamp, and turns
+ * each new snapshot into the counts of the interval since, from which
+ * per-second rates follow.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::stats::NetworkStats;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Counts over one sampling interval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsDelta {
+    pub interval_ns: u64,
+    pub stats: NetworkStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkRates {
+    pub rx_packets_per_sec: u64,
+    pub tx_packets_per_sec: u64,
+    pub rx_bytes_per_sec: u64,
+    pub tx_bytes_per_sec: u64,
+    pub rx_dropped_per_sec: u64,
+    pub tx_dropped_per_sec: u64,
+    pub rx_errors_per_sec: u64,
+    pub tx_errors_per_sec: u64,
+}
+
+impl StatsDelta {
+    /// Per-second rate of `count` events over the interval
+    pub fn per_second(&self, count: u64) -> u64 {
+        if self.interval_ns == 0 {
+            return 0;
+        }
+        (count as u128 * NANOS_PER_SECOND / self.interval_ns as u128) as u64
+    }
+
+    pub fn rates(&self) -> NetworkRates {
+        let stats = &self.stats;
+        NetworkRates {
+            rx_packets_per_sec: self.per_second(stats.rx_packets),
+            tx_packets_per_sec: self.per_second(stats.tx_packets),
+            rx_bytes_per_sec: self.per_second(stats.rx_bytes),
+            tx_bytes_per_sec: self.per_second(stats.tx_bytes),
+            rx_dropped_per_sec: self.per_second(stats.rx_dropped),
+            tx_dropped_per_sec: self.per_second(stats.tx_dropped),
+            rx_errors_per_sec: self.per_second(stats.rx_errors),
+            tx_errors_per_sec: self.per_second(stats.tx_errors),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsSampler {
+    previous: Option<(NetworkStats, u64)>,
+    last: Option<StatsDelta>,
+}
+
+impl StatsSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a snapshot taken at `now_ns`; returns the interval since the
+    /// previous one, None for the first sample or a clock that did not move
+    pub fn sample(&mut self, stats: &NetworkStats, now_ns: u64) -> Option<StatsDelta> {
+        let previous = self.previous.replace((*stats, now_ns));
+        let delta = match previous {
+            Some((earlier, then)) if now_ns > then => {
+                Some(StatsDelta { interval_ns: now_ns - then, stats: stats.delta(&earlier) })
+            }
+            _ => None,
+        };
+        if delta.is_some() {
+            self.last = delta;
+        }
+        delta
+    }
+
+    /// The most recent interval
+    pub fn last(&self) -> Option<&StatsDelta> {
+        self.last.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turns_snapshots_into_rates() {
+        let mut sampler = StatsSampler::new();
+        let mut stats = NetworkStats::default();
+        assert_eq!(sampler.sample(&stats, 1_000_000_000), None);
+
+        for _ in 0..10 {
+            stats.record_rx(0, 1000);
+        }
+        stats.record_tx_drop(0);
+        let delta = sampler.sample(&stats, 3_000_000_000).unwrap();
+        assert_eq!(delta.interval_ns, 2_000_000_000);
+        let rates = delta.rates();
+        assert_eq!((rates.rx_packets_per_sec, rates.rx_bytes_per_sec, rates.tx_dropped_per_sec), (5, 5000, 0));
+        assert_eq!(delta.per_second(delta.stats.tx_dropped * 1000), 500);
+
+        // The clock did not advance: the last interval stays available
+        assert_eq!(sampler.sample(&stats, 3_000_000_000), None);
+        assert_eq!(sampler.last(), Some(&delta));
+    }
+}
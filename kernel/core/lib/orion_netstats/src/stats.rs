@@ -0,0 +1,246 @@
+/*
+ * Orion Operating System - Network Interface Statistics
+ *
+ * NetworkStats keeps the aggregate counters every driver always had and
+ * breaks them down: packets, bytes and drops per RX and TX queue, errors
+ * by cause, and the RMON packet size distribution. The aggregates are
+ * maintained by the record_* methods, so they always equal the sum of
+ * their breakdowns. The type is Copy and allocation free so drivers can
+ * hand it out by value from interrupt paths.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+/// Queues tracked individually; traffic on higher queues counts in the last
+pub const MAX_QUEUES: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub packets: u64,
+    pub bytes: u64,
+    pub drops: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Frame check sequence mismatch
+    Crc,
+    /// Runt, oversized or truncated frame
+    Length,
+    /// No descriptor or buffer space left in the ring
+    RingOverflow,
+    /// Bus master or IOMMU fault while moving the frame
+    Dma,
+    Other,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 5] =
+        [ErrorKind::Crc, ErrorKind::Length, ErrorKind::RingOverflow, ErrorKind::Dma, ErrorKind::Other];
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorCounts {
+    counts: [u64; ErrorKind::ALL.len()],
+}
+
+impl ErrorCounts {
+    pub fn get(&self, kind: ErrorKind) -> u64 {
+        self.counts[kind as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    fn add(&mut self, kind: ErrorKind) {
+        self.counts[kind as usize] += 1;
+    }
+}
+
+/// Upper bounds of the packet size buckets (RFC 2819), the last one open
+pub const SIZE_BUCKETS: [usize; 7] = [64, 127, 255, 511, 1023, 1518, usize::MAX];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeHistogram {
+    counts: [u64; SIZE_BUCKETS.len()],
+}
+
+impl SizeHistogram {
+    pub fn record(&mut self, length: usize) {
+        let bucket = SIZE_BUCKETS.iter().position(|&limit| length <= limit).unwrap_or(SIZE_BUCKETS.len() - 1);
+        self.counts[bucket] += 1;
+    }
+
+    /// Packets per bucket, in SIZE_BUCKETS order
+    pub fn counts(&self) -> &[u64; SIZE_BUCKETS.len()] {
+        &self.counts
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub rx_queues: [QueueStats; MAX_QUEUES],
+    pub tx_queues: [QueueStats; MAX_QUEUES],
+    pub rx_error_kinds: ErrorCounts,
+    pub tx_error_kinds: ErrorCounts,
+    pub rx_sizes: SizeHistogram,
+    pub tx_sizes: SizeHistogram,
+}
+
+fn queue(queues: &mut [QueueStats; MAX_QUEUES], index: usize) -> &mut QueueStats {
+    &mut queues[index.min(MAX_QUEUES - 1)]
+}
+
+impl NetworkStats {
+    pub fn record_rx(&mut self, queue_index: usize, length: usize) {
+        let queue = queue(&mut self.rx_queues, queue_index);
+        queue.packets += 1;
+        queue.bytes += length as u64;
+        self.rx_packets += 1;
+        self.rx_bytes += length as u64;
+        self.rx_sizes.record(length);
+    }
+
+    pub fn record_tx(&mut self, queue_index: usize, length: usize) {
+        let queue = queue(&mut self.tx_queues, queue_index);
+        queue.packets += 1;
+        queue.bytes += length as u64;
+        self.tx_packets += 1;
+        self.tx_bytes += length as u64;
+        self.tx_sizes.record(length);
+    }
+
+    pub fn record_rx_drop(&mut self, queue_index: usize) {
+        queue(&mut self.rx_queues, queue_index).drops += 1;
+        self.rx_dropped += 1;
+    }
+
+    pub fn record_tx_drop(&mut self, queue_index: usize) {
+        queue(&mut self.tx_queues, queue_index).drops += 1;
+        self.tx_dropped += 1;
+    }
+
+    pub fn record_rx_error(&mut self, kind: ErrorKind) {
+        self.rx_error_kinds.add(kind);
+        self.rx_errors += 1;
+    }
+
+    pub fn record_tx_error(&mut self, kind: ErrorKind) {
+        self.tx_error_kinds.add(kind);
+        self.tx_errors += 1;
+    }
+
+    /// Apply `op` to every counter of `self` and the matching one of `other`
+    fn combine(&mut self, other: &NetworkStats, op: impl Fn(u64, u64) -> u64) {
+        for (counter, value) in [
+            (&mut self.rx_packets, other.rx_packets),
+            (&mut self.tx_packets, other.tx_packets),
+            (&mut self.rx_bytes, other.rx_bytes),
+            (&mut self.tx_bytes, other.tx_bytes),
+            (&mut self.rx_errors, other.rx_errors),
+            (&mut self.tx_errors, other.tx_errors),
+            (&mut self.rx_dropped, other.rx_dropped),
+            (&mut self.tx_dropped, other.tx_dropped),
+        ] {
+            *counter = op(*counter, value);
+        }
+        let queues =
+            self.rx_queues.iter_mut().zip(&other.rx_queues).chain(self.tx_queues.iter_mut().zip(&other.tx_queues));
+        for (queue, theirs) in queues {
+            queue.packets = op(queue.packets, theirs.packets);
+            queue.bytes = op(queue.bytes, theirs.bytes);
+            queue.drops = op(queue.drops, theirs.drops);
+        }
+        let errors = self
+            .rx_error_kinds
+            .counts
+            .iter_mut()
+            .zip(&other.rx_error_kinds.counts)
+            .chain(self.tx_error_kinds.counts.iter_mut().zip(&other.tx_error_kinds.counts));
+        let sizes = self
+            .rx_sizes
+            .counts
+            .iter_mut()
+            .zip(&other.rx_sizes.counts)
+            .chain(self.tx_sizes.counts.iter_mut().zip(&other.tx_sizes.counts));
+        for (counter, &value) in errors.chain(sizes) {
+            *counter = op(*counter, value);
+        }
+    }
+
+    /// Add another interface's counters, for system-wide totals
+    pub fn accumulate(&mut self, other: &NetworkStats) {
+        self.combine(other, u64::wrapping_add);
+    }
+
+    /// Counts since an earlier snapshot of the same counters. A counter
+    /// that went backwards was reset in between, and counts from zero.
+    pub fn delta(&self, earlier: &NetworkStats) -> NetworkStats {
+        let mut delta = *self;
+        delta.combine(earlier, |now, then| if now >= then { now - then } else { now });
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_breakdowns() {
+        let mut stats = NetworkStats::default();
+        stats.record_rx(0, 60);
+        stats.record_rx(1, 1500);
+        stats.record_rx(42, 9000);
+        stats.record_tx(0, 128);
+        stats.record_rx_drop(1);
+        stats.record_rx_error(ErrorKind::Crc);
+        stats.record_rx_error(ErrorKind::RingOverflow);
+        stats.record_tx_error(ErrorKind::Dma);
+
+        assert_eq!((stats.rx_packets, stats.rx_bytes, stats.rx_dropped), (3, 10560, 1));
+        assert_eq!(stats.rx_queues[1], QueueStats { packets: 1, bytes: 1500, drops: 1 });
+        assert_eq!(stats.rx_queues[MAX_QUEUES - 1].bytes, 9000);
+        assert_eq!(stats.rx_sizes.counts(), &[1, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(stats.tx_sizes.counts(), &[0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!((stats.rx_errors, stats.rx_error_kinds.get(ErrorKind::Crc)), (2, 1));
+        assert_eq!((stats.tx_errors, stats.tx_error_kinds.total()), (1, 1));
+    }
+
+    #[test]
+    fn test_computes_deltas_across_resets() {
+        let mut earlier = NetworkStats::default();
+        earlier.record_rx(0, 100);
+        earlier.record_rx(0, 100);
+        let mut now = earlier;
+        now.record_rx(0, 1000);
+        now.record_tx_error(ErrorKind::Length);
+
+        let delta = now.delta(&earlier);
+        assert_eq!((delta.rx_packets, delta.rx_bytes, delta.rx_queues[0].bytes), (1, 1000, 1000));
+        assert_eq!(delta.rx_sizes.counts(), &[0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(delta.tx_error_kinds.get(ErrorKind::Length), 1);
+
+        // The driver reset its counters: what it counted since is the delta
+        let mut reset = NetworkStats::default();
+        reset.record_rx(0, 64);
+        assert_eq!(reset.delta(&now).rx_bytes, 64);
+
+        let mut total = now;
+        total.accumulate(&reset);
+        assert_eq!((total.rx_packets, total.rx_sizes.counts()[0]), (4, 1));
+    }
+}
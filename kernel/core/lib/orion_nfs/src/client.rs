@@ -0,0 +1,561 @@
+/*
+ * Orion Operating System - NFSv4.1 Client Session
+ *
+ * A client of one export of an NFSv4.1 server. Connecting registers the
+ * client (EXCHANGE_ID), opens a session with a single slot
+ * (CREATE_SESSION) and looks up the export; every later COMPOUND starts
+ * with SEQUENCE on that slot, so requests are issued one at a time.
+ *
+ * Paths are relative to the export and looked up from its root in the
+ * same COMPOUND as the operation on them. Writes are unstable and
+ * committed when a file is closed or synced; if the write verifier
+ * changes in between, the server lost them and the caller is told.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::ops::*;
+use crate::rpc::{Auth, RpcClient, RpcTransport};
+use crate::NfsError;
+
+/// Largest READ or WRITE asked for, when the server allows it
+pub const MAX_IO: u32 = 1024 * 1024;
+
+/// Room for the RPC and COMPOUND headers around READ and WRITE data
+const HEADER_ROOM: u32 = 1024;
+
+/// Largest READDIR reply asked for
+const MAX_READDIR: u32 = 32 * 1024;
+
+/// A file opened on the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFile {
+    pub handle: FileHandle,
+    pub attributes: Attributes,
+    stateid: Stateid,
+    /// Verifier of writes not committed yet
+    unstable: Option<Verifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub attributes: Attributes,
+}
+
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|component| !component.is_empty())
+}
+
+/// Ops making the file at `path` under `root` the current file handle,
+/// and how many LOOKUP results they produce
+fn walk<'a>(ops: &mut Vec<Op<'a>>, root: &'a FileHandle, path: &'a str) -> usize {
+    ops.push(Op::PutFh(root));
+    let before = ops.len();
+    ops.extend(components(path).map(Op::Lookup));
+    ops.len() - before
+}
+
+fn skip_walk(reply: &mut CompoundReply, lookups: usize) -> Result<(), NfsError> {
+    reply.next(OP_PUTFH)?;
+    for _ in 0..lookups {
+        reply.next(OP_LOOKUP)?;
+    }
+    Ok(())
+}
+
+/// Run a COMPOUND that needs no session
+fn call<T: RpcTransport>(rpc: &mut RpcClient<T>, ops: &[Op]) -> Result<Vec<u8>, NfsError> {
+    rpc.call(NFS_PROGRAM, NFS_VERSION, PROC_COMPOUND, &compound(ops))
+}
+
+pub struct Client<T: RpcTransport> {
+    rpc: RpcClient<T>,
+    client_id: u64,
+    session: SessionId,
+    /// Sequence of the next request on slot 0
+    sequence: u32,
+    owner: Vec<u8>,
+    root: FileHandle,
+    max_read: u32,
+    max_write: u32,
+}
+
+impl<T: RpcTransport> Client<T> {
+    /// Establish a session and look up `export`. `owner` must be unique
+    /// to this client and stable across its restarts; `verifier` changes
+    /// on every restart, so the server drops the state of the last one.
+    pub fn connect(transport: T, auth: Auth, owner: &[u8], verifier: Verifier, export: &str) -> Result<Self, NfsError> {
+        let first_xid = u32::from_be_bytes([verifier[0], verifier[1], verifier[2], verifier[3]]);
+        let mut rpc = RpcClient::new(transport, auth, first_xid);
+
+        let results = call(&mut rpc, &[Op::ExchangeId { verifier, owner }])?;
+        let (client_id, sequence) = decode_exchange_id(CompoundReply::decode(&results)?.next(OP_EXCHANGE_ID)?)?;
+
+        let limit = MAX_IO + HEADER_ROOM;
+        let results =
+            call(&mut rpc, &[Op::CreateSession { client_id, sequence, max_request: limit, max_response: limit }])?;
+        let info = decode_create_session(CompoundReply::decode(&results)?.next(OP_CREATE_SESSION)?)?;
+        let max_read = info.max_response.saturating_sub(HEADER_ROOM).min(MAX_IO);
+        let max_write = info.max_request.saturating_sub(HEADER_ROOM).min(MAX_IO);
+        if max_read == 0 || max_write == 0 {
+            return Err(NfsError::Unsupported);
+        }
+
+        let mut client = Self {
+            rpc,
+            client_id,
+            session: info.session,
+            sequence: 1,
+            owner: owner.to_vec(),
+            root: FileHandle::default(),
+            max_read,
+            max_write,
+        };
+        // No state to reclaim: a new client id holds none
+        client.compound(&[Op::ReclaimComplete], |reply| reply.next(OP_RECLAIM_COMPLETE).map(|_| ()))?;
+
+        let mut ops = Vec::from([Op::PutRootFh]);
+        ops.extend(components(export).map(Op::Lookup));
+        let lookups = ops.len() - 1;
+        ops.push(Op::GetFh);
+        client.root = client.compound(&ops, |reply| {
+            reply.next(OP_PUTROOTFH)?;
+            for _ in 0..lookups {
+                reply.next(OP_LOOKUP)?;
+            }
+            decode_file_handle(reply.next(OP_GETFH)?)
+        })?;
+        Ok(client)
+    }
+
+    /// Largest read and write the session carries
+    pub fn max_io(&self) -> (u32, u32) {
+        (self.max_read, self.max_write)
+    }
+
+    /// Run `ops` after SEQUENCE and decode their results with `results`
+    fn compound<R>(
+        &mut self,
+        ops: &[Op],
+        results: impl FnOnce(&mut CompoundReply) -> Result<R, NfsError>,
+    ) -> Result<R, NfsError> {
+        let mut all = Vec::with_capacity(ops.len() + 1);
+        all.push(Op::Sequence { session: self.session, sequence: self.sequence });
+        all.extend_from_slice(ops);
+        let bytes = call(&mut self.rpc, &all)?;
+        let mut reply = CompoundReply::decode(&bytes)?;
+        decode_sequence(reply.next(OP_SEQUENCE)?)?;
+        // The slot moves on once SEQUENCE succeeded, whatever follows
+        self.sequence = self.sequence.wrapping_add(1);
+        results(&mut reply)
+    }
+
+    pub fn getattr(&mut self, path: &str) -> Result<Attributes, NfsError> {
+        let root = self.root.clone();
+        let mut ops = Vec::new();
+        let lookups = walk(&mut ops, &root, path);
+        ops.push(Op::GetAttr);
+        self.compound(&ops, |reply| {
+            skip_walk(reply, lookups)?;
+            decode_attributes(reply.next(OP_GETATTR)?)
+        })
+    }
+
+    /// Open the file at `path` with `access` (SHARE_ACCESS_*), creating
+    /// it with mode `create` when given and the file is missing
+    pub fn open(&mut self, path: &str, access: u32, create: Option<u32>) -> Result<OpenFile, NfsError> {
+        let (parent, name) = match path.trim_end_matches('/').rsplit_once('/') {
+            Some((parent, name)) => (parent, name),
+            None => ("", path),
+        };
+        if name.is_empty() {
+            return Err(NfsError::Status(NFS4ERR_ISDIR));
+        }
+        let root = self.root.clone();
+        let owner = self.owner.clone();
+        let mut ops = Vec::new();
+        let lookups = walk(&mut ops, &root, parent);
+        ops.push(Op::Open { client_id: self.client_id, owner: &owner, name, access, create });
+        ops.push(Op::GetFh);
+        ops.push(Op::GetAttr);
+        self.compound(&ops, |reply| {
+            skip_walk(reply, lookups)?;
+            let stateid = decode_open(reply.next(OP_OPEN)?)?;
+            let handle = decode_file_handle(reply.next(OP_GETFH)?)?;
+            let attributes = decode_attributes(reply.next(OP_GETATTR)?)?;
+            Ok(OpenFile { handle, attributes, stateid, unstable: None })
+        })
+    }
+
+    /// Read up to `count` bytes at `offset`, at most max_io at once; the
+    /// flag tells whether the data reaches the end of the file
+    pub fn read(&mut self, file: &OpenFile, offset: u64, count: u32) -> Result<(Vec<u8>, bool), NfsError> {
+        let count = count.min(self.max_read);
+        let ops = [Op::PutFh(&file.handle), Op::Read { stateid: file.stateid, offset, count }];
+        self.compound(&ops, |reply| {
+            reply.next(OP_PUTFH)?;
+            decode_read(reply.next(OP_READ)?, count as usize)
+        })
+    }
+
+    /// Write the head of `data` at `offset` and return how much of it the
+    /// server took
+    pub fn write(&mut self, file: &mut OpenFile, offset: u64, data: &[u8]) -> Result<usize, NfsError> {
+        let data = &data[..data.len().min(self.max_write as usize)];
+        let ops = [Op::PutFh(&file.handle), Op::Write { stateid: file.stateid, offset, stable: UNSTABLE, data }];
+        let (count, committed, verifier) = self.compound(&ops, |reply| {
+            reply.next(OP_PUTFH)?;
+            decode_write(reply.next(OP_WRITE)?)
+        })?;
+        if count as usize > data.len() {
+            return Err(NfsError::Malformed);
+        }
+        match file.unstable {
+            Some(previous) if previous != verifier => return Err(NfsError::WritesLost),
+            _ if committed == UNSTABLE => file.unstable = Some(verifier),
+            _ => {}
+        }
+        Ok(count as usize)
+    }
+
+    /// Make the writes to `file` stable
+    pub fn commit(&mut self, file: &mut OpenFile) -> Result<(), NfsError> {
+        let Some(expected) = file.unstable else {
+            return Ok(());
+        };
+        let ops = [Op::PutFh(&file.handle), Op::Commit];
+        let verifier = self.compound(&ops, |reply| {
+            reply.next(OP_PUTFH)?;
+            decode_commit(reply.next(OP_COMMIT)?)
+        })?;
+        file.unstable = None;
+        if verifier != expected {
+            return Err(NfsError::WritesLost);
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self, mut file: OpenFile) -> Result<(), NfsError> {
+        let committed = self.commit(&mut file);
+        let ops = [Op::PutFh(&file.handle), Op::Close(file.stateid)];
+        self.compound(&ops, |reply| {
+            reply.next(OP_PUTFH)?;
+            decode_close(reply.next(OP_CLOSE)?)
+        })?;
+        committed
+    }
+
+    /// Entries of the directory at `path`, in the order of the server
+    pub fn read_dir(&mut self, path: &str) -> Result<Vec<DirEntry>, NfsError> {
+        let root = self.root.clone();
+        let mut entries = Vec::new();
+        let mut cookie = 0;
+        let mut verifier = [0u8; 8];
+        loop {
+            let mut ops = Vec::new();
+            let lookups = walk(&mut ops, &root, path);
+            ops.push(Op::ReadDir { cookie, verifier, max: MAX_READDIR.min(self.max_read) });
+            let (batch, next_verifier, eof) = self.compound(&ops, |reply| {
+                skip_walk(reply, lookups)?;
+                decode_readdir(reply.next(OP_READDIR)?)
+            })?;
+            // A server that returns nothing before the end would loop forever
+            if batch.is_empty() && !eof {
+                return Err(NfsError::Malformed);
+            }
+            verifier = next_verifier;
+            if let Some(last) = batch.last() {
+                cookie = last.cookie;
+            }
+            entries.extend(batch.into_iter().map(|entry| DirEntry { name: entry.name, attributes: entry.attributes }));
+            if eof {
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// Tear down the session and the client id; open files are closed
+    /// by the server with them
+    pub fn disconnect(mut self) -> Result<(), NfsError> {
+        let results = call(&mut self.rpc, &[Op::DestroySession(self.session)])?;
+        CompoundReply::decode(&results)?.next(OP_DESTROY_SESSION)?;
+        let results = call(&mut self.rpc, &[Op::DestroyClientId(self.client_id)])?;
+        CompoundReply::decode(&results)?.next(OP_DESTROY_CLIENTID).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::{frame, unframe};
+    use crate::xdr::{XdrReader, XdrWriter};
+    use alloc::vec;
+
+    const SESSION: SessionId = [7; 16];
+    const VERIFIER: Verifier = [9; 8];
+    const ROOT: u8 = 0;
+    const EXPORT: u8 = 1;
+    /// File handles of files are their index plus this
+    const FIRST_FILE: u8 = 2;
+
+    /// One-directory NFSv4.1 server at /export, replying to each call as
+    /// it is sent
+    struct MockServer {
+        files: Vec<(String, Vec<u8>)>,
+        sequence: u32,
+        current: u8,
+        replies: Vec<u8>,
+    }
+
+    fn attributes(writer: &mut XdrWriter, file_type: u32, size: u64, id: u64) {
+        let mut values = XdrWriter::new();
+        values.u32(file_type).u64(size).u64(id).u32(0o644).u64(0).u32(0);
+        writer.u32(2).u32(1 << ATTR_TYPE | 1 << ATTR_SIZE | 1 << ATTR_FILEID);
+        writer.u32(1 << (ATTR_MODE - 32) | 1 << (ATTR_TIME_MODIFY - 32)).opaque(&values.into_bytes());
+    }
+
+    fn skip_bitmap(args: &mut XdrReader) {
+        let count = args.u32().unwrap();
+        for _ in 0..count {
+            args.u32().unwrap();
+        }
+    }
+
+    fn channel(writer: &mut XdrWriter, max: u32) {
+        writer.u32(0).u32(max).u32(max).u32(max).u32(16).u32(1).u32(0);
+    }
+
+    impl MockServer {
+        /// Result of one operation, or its failure status
+        fn run(&mut self, op: u32, args: &mut XdrReader, out: &mut XdrWriter) -> u32 {
+            match op {
+                OP_EXCHANGE_ID => {
+                    args.fixed(8).unwrap();
+                    args.opaque(1024).unwrap();
+                    for _ in 0..3 {
+                        args.u32().unwrap();
+                    }
+                    out.u64(0x1234).u32(1).u32(0).u32(0).u64(0).opaque(b"mock").opaque(b"mock").u32(0);
+                }
+                OP_CREATE_SESSION => {
+                    assert_eq!(args.u64(), Ok(0x1234));
+                    assert_eq!(args.u32(), Ok(1));
+                    for _ in 0..(1 + 7 + 7 + 1 + 2) {
+                        args.u32().unwrap();
+                    }
+                    out.fixed(&SESSION).u32(1).u32(0);
+                    // Replies of at most 8 KiB of data
+                    channel(out, 8192 + HEADER_ROOM);
+                    channel(out, 4096);
+                }
+                OP_DESTROY_SESSION => {
+                    assert_eq!(args.fixed(16), Ok(&SESSION[..]));
+                }
+                OP_DESTROY_CLIENTID => {
+                    assert_eq!(args.u64(), Ok(0x1234));
+                }
+                OP_SEQUENCE => {
+                    assert_eq!(args.fixed(16), Ok(&SESSION[..]));
+                    assert_eq!(args.u32(), Ok(self.sequence));
+                    for _ in 0..3 {
+                        args.u32().unwrap();
+                    }
+                    out.fixed(&SESSION).u32(self.sequence).u32(0).u32(0).u32(0).u32(0);
+                    self.sequence += 1;
+                }
+                OP_RECLAIM_COMPLETE => {
+                    args.bool().unwrap();
+                }
+                OP_PUTROOTFH => self.current = ROOT,
+                OP_PUTFH => self.current = args.opaque(128).unwrap()[0],
+                OP_LOOKUP => {
+                    let name = args.string(255).unwrap();
+                    match self.current {
+                        ROOT if name == "export" => self.current = EXPORT,
+                        EXPORT => match self.files.iter().position(|(file, _)| *file == name) {
+                            Some(index) => self.current = FIRST_FILE + index as u8,
+                            None => return NFS4ERR_NOENT,
+                        },
+                        _ => return NFS4ERR_NOENT,
+                    }
+                }
+                OP_GETFH => {
+                    out.opaque(&[self.current]);
+                }
+                OP_GETATTR => {
+                    skip_bitmap(args);
+                    match self.current.checked_sub(FIRST_FILE) {
+                        Some(index) => {
+                            attributes(out, 1, self.files[index as usize].1.len() as u64, self.current as u64)
+                        }
+                        None => attributes(out, 2, 0, self.current as u64),
+                    }
+                }
+                OP_OPEN => {
+                    args.u32().unwrap();
+                    assert_eq!(args.u32().unwrap() & 0x0400, 0x0400);
+                    args.u32().unwrap();
+                    args.u64().unwrap();
+                    args.opaque(1024).unwrap();
+                    let create = args.u32().unwrap() == 1;
+                    if create {
+                        args.u32().unwrap();
+                        skip_bitmap(args);
+                        args.opaque(4096).unwrap();
+                    }
+                    assert_eq!(args.u32(), Ok(0));
+                    let name = args.string(255).unwrap();
+                    let index = match self.files.iter().position(|(file, _)| *file == name) {
+                        Some(index) => index,
+                        None if create => {
+                            self.files.push((name, Vec::new()));
+                            self.files.len() - 1
+                        }
+                        None => return NFS4ERR_NOENT,
+                    };
+                    self.current = FIRST_FILE + index as u8;
+                    out.u32(1).fixed(&[self.current; 12]).bool(false).u64(0).u64(0).u32(0).u32(0).u32(0);
+                }
+                OP_CLOSE => {
+                    args.u32().unwrap();
+                    args.u32().unwrap();
+                    let other = args.fixed(12).unwrap();
+                    assert_eq!(other[0], self.current);
+                    out.u32(2).fixed(other);
+                }
+                OP_READ => {
+                    args.u32().unwrap();
+                    args.fixed(12).unwrap();
+                    let offset = args.u64().unwrap() as usize;
+                    let count = args.u32().unwrap() as usize;
+                    let data = &self.files[(self.current - FIRST_FILE) as usize].1;
+                    let end = data.len().min(offset + count);
+                    out.bool(end == data.len()).opaque(&data[offset.min(end)..end]);
+                }
+                OP_WRITE => {
+                    args.u32().unwrap();
+                    args.fixed(12).unwrap();
+                    let offset = args.u64().unwrap() as usize;
+                    assert_eq!(args.u32(), Ok(UNSTABLE));
+                    let data = args.opaque(1 << 20).unwrap();
+                    let file = &mut self.files[(self.current - FIRST_FILE) as usize].1;
+                    if file.len() < offset + data.len() {
+                        file.resize(offset + data.len(), 0);
+                    }
+                    file[offset..offset + data.len()].copy_from_slice(data);
+                    out.u32(data.len() as u32).u32(UNSTABLE).fixed(&VERIFIER);
+                }
+                OP_COMMIT => {
+                    args.u64().unwrap();
+                    args.u32().unwrap();
+                    out.fixed(&VERIFIER);
+                }
+                OP_READDIR => {
+                    // One entry per reply, cookies start past the reserved ones
+                    let cookie = args.u64().unwrap();
+                    args.fixed(8).unwrap();
+                    args.u32().unwrap();
+                    args.u32().unwrap();
+                    skip_bitmap(args);
+                    let index = cookie.saturating_sub(2) as usize;
+                    out.fixed(&[1; 8]);
+                    if let Some((name, data)) = self.files.get(index) {
+                        out.bool(true).u64(index as u64 + 3).string(name);
+                        attributes(out, 1, data.len() as u64, index as u64 + FIRST_FILE as u64);
+                    }
+                    out.bool(false).bool(index + 1 >= self.files.len());
+                }
+                _ => panic!("unexpected operation {}", op),
+            }
+            NFS4_OK
+        }
+    }
+
+    impl RpcTransport for MockServer {
+        fn send(&mut self, data: &[u8]) -> Result<(), NfsError> {
+            let (call, _) = unframe(data).unwrap().unwrap();
+            let mut args = XdrReader::new(&call);
+            let xid = args.u32().unwrap();
+            for _ in 0..5 {
+                args.u32().unwrap();
+            }
+            for _ in 0..2 {
+                args.u32().unwrap();
+                args.opaque(400).unwrap();
+            }
+            args.opaque(0).unwrap();
+            assert_eq!(args.u32(), Ok(MINOR_VERSION));
+            let count = args.u32().unwrap();
+
+            let mut results = XdrWriter::new();
+            let mut status = NFS4_OK;
+            let mut done = 0;
+            for _ in 0..count {
+                let op = args.u32().unwrap();
+                let mut out = XdrWriter::new();
+                status = self.run(op, &mut args, &mut out);
+                results.u32(op).u32(status);
+                done += 1;
+                if status != NFS4_OK {
+                    break;
+                }
+                results.raw(&out.into_bytes());
+            }
+
+            let mut reply = XdrWriter::new();
+            reply.u32(xid).u32(1).u32(0).u32(0).u32(0).u32(0);
+            reply.u32(status).string("").u32(done).raw(&results.into_bytes());
+            self.replies.extend_from_slice(&frame(&reply.into_bytes()));
+            Ok(())
+        }
+
+        fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, NfsError> {
+            let length = buffer.len().min(self.replies.len());
+            buffer[..length].copy_from_slice(&self.replies[..length]);
+            self.replies.drain(..length);
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn test_reads_and_writes_files_of_an_export() {
+        let big: Vec<u8> = (0..10000u32).map(|value| value as u8).collect();
+        let server = MockServer {
+            files: vec![(String::from("big.bin"), big.clone())],
+            sequence: 1,
+            current: 0,
+            replies: vec![],
+        };
+        let mut client = Client::connect(server, Auth::None, b"orion-test", [1; 8], "/export").unwrap();
+        assert_eq!(client.max_io(), (8192, 8192));
+
+        let mut file = client.open("notes.txt", SHARE_ACCESS_BOTH, Some(0o644)).unwrap();
+        assert_eq!(file.attributes.size, 0);
+        assert_eq!(client.write(&mut file, 0, b"hello nfs"), Ok(9));
+        client.close(file).unwrap();
+        assert_eq!(client.getattr("/notes.txt").map(|attributes| attributes.size), Ok(9));
+        assert_eq!(client.getattr("/missing"), Err(NfsError::Status(NFS4ERR_NOENT)));
+
+        // Reads are split at the 8 KiB the session carries
+        let file = client.open("/big.bin", SHARE_ACCESS_READ, None).unwrap();
+        assert_eq!(file.attributes.file_type, FileType::Regular);
+        let (head, eof) = client.read(&file, 0, 16384).unwrap();
+        assert_eq!((head.len(), eof), (8192, false));
+        let (tail, eof) = client.read(&file, 8192, 16384).unwrap();
+        assert_eq!((tail.len(), eof), (1808, true));
+        assert_eq!([head, tail].concat(), big);
+        client.close(file).unwrap();
+
+        let names: Vec<String> = client.read_dir("/").unwrap().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["big.bin", "notes.txt"]);
+        client.disconnect().unwrap();
+    }
+}
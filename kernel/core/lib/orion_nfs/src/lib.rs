@@ -0,0 +1,50 @@
+/*
+ * Orion Operating System - NFSv4.1 Client
+ *
+ * Network file system client for corporate file shares: XDR encoding,
+ * ONC RPC calls over a stream transport, the NFSv4.1 operations Orion
+ * needs, and a client that establishes a session with the server and
+ * reads and writes files through COMPOUND requests. Delegations are
+ * never requested, so the client has no callback channel to serve.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod client;
+pub mod ops;
+pub mod rpc;
+pub mod xdr;
+
+pub use client::{Client, DirEntry, OpenFile};
+pub use ops::{Attributes, FileHandle, FileType};
+pub use rpc::{Auth, RpcClient, RpcTransport};
+
+/// TCP port of NFS
+pub const NFS_PORT: u16 = 2049;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NfsError {
+    /// The connection to the server failed or was closed
+    Transport,
+    /// The server sent something that does not decode
+    Malformed,
+    /// The server refused the RPC call (MSG_DENIED)
+    Rejected,
+    /// The server accepted the call but did not run it (accept_stat)
+    Rpc(u32),
+    /// An operation failed with this nfsstat4
+    Status(u32),
+    /// The server lacks something the client needs
+    Unsupported,
+    /// The server restarted before unstable writes were committed, so
+    /// they have to be written again
+    WritesLost,
+}
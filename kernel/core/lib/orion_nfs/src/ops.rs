@@ -0,0 +1,600 @@
+/*
+ * Orion Operating System - NFSv4.1 Operations
+ *
+ * Arguments and results of the NFSv4.1 operations (RFC 8881) the client
+ * issues, all carried by the single COMPOUND procedure: a request names
+ * a list of operations run in order against a current file handle, and
+ * the reply holds one result per operation up to the first that failed.
+ *
+ * Only the attributes the VFS needs are asked for (type, size, file id,
+ * mode and modification time), and opens always tell the server the
+ * client wants no delegation.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::xdr::{XdrReader, XdrWriter};
+use crate::NfsError;
+
+// ONC RPC program of NFS
+pub const NFS_PROGRAM: u32 = 100003;
+pub const NFS_VERSION: u32 = 4;
+pub const PROC_COMPOUND: u32 = 1;
+pub const MINOR_VERSION: u32 = 1;
+
+// Operation numbers
+pub const OP_CLOSE: u32 = 4;
+pub const OP_COMMIT: u32 = 5;
+pub const OP_GETATTR: u32 = 9;
+pub const OP_GETFH: u32 = 10;
+pub const OP_LOOKUP: u32 = 15;
+pub const OP_OPEN: u32 = 18;
+pub const OP_PUTFH: u32 = 22;
+pub const OP_PUTROOTFH: u32 = 24;
+pub const OP_READ: u32 = 25;
+pub const OP_READDIR: u32 = 26;
+pub const OP_WRITE: u32 = 38;
+pub const OP_EXCHANGE_ID: u32 = 42;
+pub const OP_CREATE_SESSION: u32 = 43;
+pub const OP_DESTROY_SESSION: u32 = 44;
+pub const OP_SEQUENCE: u32 = 53;
+pub const OP_DESTROY_CLIENTID: u32 = 57;
+pub const OP_RECLAIM_COMPLETE: u32 = 58;
+
+// nfsstat4 values the VFS tells apart
+pub const NFS4_OK: u32 = 0;
+pub const NFS4ERR_PERM: u32 = 1;
+pub const NFS4ERR_NOENT: u32 = 2;
+pub const NFS4ERR_IO: u32 = 5;
+pub const NFS4ERR_ACCESS: u32 = 13;
+pub const NFS4ERR_EXIST: u32 = 17;
+pub const NFS4ERR_NOTDIR: u32 = 20;
+pub const NFS4ERR_ISDIR: u32 = 21;
+pub const NFS4ERR_NOSPC: u32 = 28;
+pub const NFS4ERR_ROFS: u32 = 30;
+
+// Attribute numbers
+pub const ATTR_TYPE: u32 = 1;
+pub const ATTR_SIZE: u32 = 4;
+pub const ATTR_FILEID: u32 = 20;
+pub const ATTR_MODE: u32 = 33;
+pub const ATTR_TIME_MODIFY: u32 = 53;
+
+/// Attributes asked for by GETATTR and READDIR
+pub const REQUESTED_ATTRIBUTES: [u32; 5] = [ATTR_TYPE, ATTR_SIZE, ATTR_FILEID, ATTR_MODE, ATTR_TIME_MODIFY];
+
+// OPEN share access
+pub const SHARE_ACCESS_READ: u32 = 1;
+pub const SHARE_ACCESS_WRITE: u32 = 2;
+pub const SHARE_ACCESS_BOTH: u32 = 3;
+const SHARE_ACCESS_WANT_NO_DELEG: u32 = 0x0400;
+const SHARE_DENY_NONE: u32 = 0;
+
+const OPEN4_NOCREATE: u32 = 0;
+const OPEN4_CREATE: u32 = 1;
+const CREATE_UNCHECKED: u32 = 0;
+const CLAIM_NULL: u32 = 0;
+
+const OPEN_DELEGATE_NONE: u32 = 0;
+const OPEN_DELEGATE_NONE_EXT: u32 = 3;
+const WND4_CONTENTION: u32 = 1;
+const WND4_RESOURCE: u32 = 2;
+
+// WRITE stability
+pub const UNSTABLE: u32 = 0;
+pub const DATA_SYNC: u32 = 1;
+pub const FILE_SYNC: u32 = 2;
+
+const EXCHGID4_FLAG_USE_NON_PNFS: u32 = 0x0001_0000;
+const SP4_NONE: u32 = 0;
+
+const NFS4_FHSIZE: usize = 128;
+const NFS4_OPAQUE_LIMIT: usize = 1024;
+const NFS4_SESSIONID_SIZE: usize = 16;
+const NFS4_VERIFIER_SIZE: usize = 8;
+const MAX_BITMAP_WORDS: usize = 3;
+const MAX_ATTRIBUTES: usize = 4096;
+pub const MAX_NAME: usize = 255;
+
+pub type SessionId = [u8; NFS4_SESSIONID_SIZE];
+pub type Verifier = [u8; NFS4_VERIFIER_SIZE];
+
+/// Opaque server handle of a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileHandle(pub Vec<u8>);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stateid {
+    pub seqid: u32,
+    pub other: [u8; 12],
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Directory,
+    Symlink,
+    #[default]
+    Other,
+}
+
+impl FileType {
+    pub fn from_raw(value: u32) -> Self {
+        match value {
+            1 => FileType::Regular,
+            2 => FileType::Directory,
+            5 => FileType::Symlink,
+            _ => FileType::Other,
+        }
+    }
+
+    pub fn raw(self) -> u32 {
+        match self {
+            FileType::Regular => 1,
+            FileType::Directory => 2,
+            FileType::Symlink => 5,
+            FileType::Other => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pub file_type: FileType,
+    pub size: u64,
+    pub file_id: u64,
+    pub mode: u32,
+    /// Seconds since the Unix epoch
+    pub modified: i64,
+}
+
+/// Fore channel limits agreed by CREATE_SESSION
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub session: SessionId,
+    pub max_request: u32,
+    pub max_response: u32,
+}
+
+/// A READDIR entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub cookie: u64,
+    pub name: String,
+    pub attributes: Attributes,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op<'a> {
+    ExchangeId {
+        verifier: Verifier,
+        owner: &'a [u8],
+    },
+    CreateSession {
+        client_id: u64,
+        sequence: u32,
+        max_request: u32,
+        max_response: u32,
+    },
+    DestroySession(SessionId),
+    DestroyClientId(u64),
+    Sequence {
+        session: SessionId,
+        sequence: u32,
+    },
+    ReclaimComplete,
+    PutRootFh,
+    PutFh(&'a FileHandle),
+    Lookup(&'a str),
+    GetFh,
+    GetAttr,
+    /// Open `name` in the current directory, creating it with `create`
+    /// as mode when missing
+    Open {
+        client_id: u64,
+        owner: &'a [u8],
+        name: &'a str,
+        access: u32,
+        create: Option<u32>,
+    },
+    Close(Stateid),
+    Read {
+        stateid: Stateid,
+        offset: u64,
+        count: u32,
+    },
+    Write {
+        stateid: Stateid,
+        offset: u64,
+        stable: u32,
+        data: &'a [u8],
+    },
+    Commit,
+    ReadDir {
+        cookie: u64,
+        verifier: Verifier,
+        max: u32,
+    },
+}
+
+fn encode_bitmap(writer: &mut XdrWriter, attributes: &[u32]) {
+    let mut words = [0u32; MAX_BITMAP_WORDS];
+    for attribute in attributes {
+        words[*attribute as usize / 32] |= 1 << (attribute % 32);
+    }
+    let count = words.iter().rposition(|word| *word != 0).map_or(0, |last| last + 1);
+    writer.u32(count as u32);
+    for word in &words[..count] {
+        writer.u32(*word);
+    }
+}
+
+fn encode_channel(writer: &mut XdrWriter, max_request: u32, max_response: u32) {
+    // headerpadsize, maxrequestsize, maxresponsesize, maxresponsesize_cached,
+    // maxoperations, maxrequests and no RDMA
+    writer.u32(0).u32(max_request).u32(max_response).u32(max_response).u32(16).u32(1).u32(0);
+}
+
+fn encode_stateid(writer: &mut XdrWriter, stateid: &Stateid) {
+    writer.u32(stateid.seqid).fixed(&stateid.other);
+}
+
+impl Op<'_> {
+    pub fn number(&self) -> u32 {
+        match self {
+            Op::ExchangeId { .. } => OP_EXCHANGE_ID,
+            Op::CreateSession { .. } => OP_CREATE_SESSION,
+            Op::DestroySession(_) => OP_DESTROY_SESSION,
+            Op::DestroyClientId(_) => OP_DESTROY_CLIENTID,
+            Op::Sequence { .. } => OP_SEQUENCE,
+            Op::ReclaimComplete => OP_RECLAIM_COMPLETE,
+            Op::PutRootFh => OP_PUTROOTFH,
+            Op::PutFh(_) => OP_PUTFH,
+            Op::Lookup(_) => OP_LOOKUP,
+            Op::GetFh => OP_GETFH,
+            Op::GetAttr => OP_GETATTR,
+            Op::Open { .. } => OP_OPEN,
+            Op::Close(_) => OP_CLOSE,
+            Op::Read { .. } => OP_READ,
+            Op::Write { .. } => OP_WRITE,
+            Op::Commit => OP_COMMIT,
+            Op::ReadDir { .. } => OP_READDIR,
+        }
+    }
+
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.number());
+        match self {
+            Op::ExchangeId { verifier, owner } => {
+                // No state protection and no implementation id
+                writer.fixed(verifier).opaque(owner).u32(EXCHGID4_FLAG_USE_NON_PNFS).u32(SP4_NONE).u32(0);
+            }
+            Op::CreateSession { client_id, sequence, max_request, max_response } => {
+                writer.u64(*client_id).u32(*sequence).u32(0);
+                encode_channel(writer, *max_request, *max_response);
+                // The back channel is never used without delegations
+                encode_channel(writer, 4096, 4096);
+                // Callback program, with AUTH_NONE as its only flavor
+                writer.u32(0x4000_0000).u32(1).u32(0);
+            }
+            Op::DestroySession(session) => {
+                writer.fixed(session);
+            }
+            Op::DestroyClientId(client_id) => {
+                writer.u64(*client_id);
+            }
+            Op::Sequence { session, sequence } => {
+                // Slot 0 is the only slot, and replies are not cached
+                writer.fixed(session).u32(*sequence).u32(0).u32(0).bool(false);
+            }
+            Op::ReclaimComplete => {
+                writer.bool(false);
+            }
+            Op::Commit => {
+                // The whole file
+                writer.u64(0).u32(0);
+            }
+            Op::PutRootFh | Op::GetFh => {}
+            Op::PutFh(handle) => {
+                writer.opaque(&handle.0);
+            }
+            Op::Lookup(name) => {
+                writer.string(name);
+            }
+            Op::GetAttr => encode_bitmap(writer, &REQUESTED_ATTRIBUTES),
+            Op::Open { client_id, owner, name, access, create } => {
+                writer.u32(0).u32(access | SHARE_ACCESS_WANT_NO_DELEG).u32(SHARE_DENY_NONE);
+                writer.u64(*client_id).opaque(owner);
+                match create {
+                    Some(mode) => {
+                        writer.u32(OPEN4_CREATE).u32(CREATE_UNCHECKED);
+                        encode_bitmap(writer, &[ATTR_MODE]);
+                        writer.u32(4).u32(*mode);
+                    }
+                    None => {
+                        writer.u32(OPEN4_NOCREATE);
+                    }
+                }
+                writer.u32(CLAIM_NULL).string(name);
+            }
+            Op::Close(stateid) => {
+                writer.u32(0);
+                encode_stateid(writer, stateid);
+            }
+            Op::Read { stateid, offset, count } => {
+                encode_stateid(writer, stateid);
+                writer.u64(*offset).u32(*count);
+            }
+            Op::Write { stateid, offset, stable, data } => {
+                encode_stateid(writer, stateid);
+                writer.u64(*offset).u32(*stable).opaque(data);
+            }
+            Op::ReadDir { cookie, verifier, max } => {
+                writer.u64(*cookie).fixed(verifier).u32(*max).u32(*max);
+                encode_bitmap(writer, &REQUESTED_ATTRIBUTES);
+            }
+        }
+    }
+}
+
+/// Arguments of a COMPOUND running `ops`
+pub fn compound(ops: &[Op]) -> Vec<u8> {
+    let mut writer = XdrWriter::new();
+    writer.string("").u32(MINOR_VERSION).u32(ops.len() as u32);
+    for op in ops {
+        op.encode(&mut writer);
+    }
+    writer.into_bytes()
+}
+
+/// Results of a COMPOUND, taken one operation at a time
+pub struct CompoundReply<'a> {
+    reader: XdrReader<'a>,
+    status: u32,
+    remaining: u32,
+}
+
+impl<'a> CompoundReply<'a> {
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, NfsError> {
+        let mut reader = XdrReader::new(bytes);
+        let status = reader.u32()?;
+        reader.opaque(NFS4_OPAQUE_LIMIT)?;
+        let remaining = reader.u32()?;
+        Ok(Self { reader, status, remaining })
+    }
+
+    /// Reader over the result of the next operation, which must be `op`;
+    /// fails with the status of the operation when it did not succeed
+    pub fn next(&mut self, op: u32) -> Result<&mut XdrReader<'a>, NfsError> {
+        if self.remaining == 0 {
+            return Err(match self.status {
+                NFS4_OK => NfsError::Malformed,
+                status => NfsError::Status(status),
+            });
+        }
+        self.remaining -= 1;
+        if self.reader.u32()? != op {
+            return Err(NfsError::Malformed);
+        }
+        match self.reader.u32()? {
+            NFS4_OK => Ok(&mut self.reader),
+            status => Err(NfsError::Status(status)),
+        }
+    }
+}
+
+fn skip_bitmap(reader: &mut XdrReader) -> Result<(), NfsError> {
+    let count = reader.u32()? as usize;
+    if count > MAX_BITMAP_WORDS {
+        return Err(NfsError::Malformed);
+    }
+    for _ in 0..count {
+        reader.u32()?;
+    }
+    Ok(())
+}
+
+fn decode_stateid(reader: &mut XdrReader) -> Result<Stateid, NfsError> {
+    let seqid = reader.u32()?;
+    let mut other = [0u8; 12];
+    other.copy_from_slice(reader.fixed(12)?);
+    Ok(Stateid { seqid, other })
+}
+
+fn decode_verifier(reader: &mut XdrReader) -> Result<Verifier, NfsError> {
+    let mut verifier = [0u8; NFS4_VERIFIER_SIZE];
+    verifier.copy_from_slice(reader.fixed(NFS4_VERIFIER_SIZE)?);
+    Ok(verifier)
+}
+
+/// Max request and response sizes of a channel
+fn decode_channel(reader: &mut XdrReader) -> Result<(u32, u32), NfsError> {
+    reader.u32()?;
+    let limits = (reader.u32()?, reader.u32()?);
+    for _ in 0..3 {
+        reader.u32()?;
+    }
+    match reader.u32()? {
+        0 => {}
+        1 => {
+            reader.u32()?;
+        }
+        _ => return Err(NfsError::Malformed),
+    }
+    Ok(limits)
+}
+
+/// Client id and the sequence CREATE_SESSION must carry
+pub fn decode_exchange_id(reader: &mut XdrReader) -> Result<(u64, u32), NfsError> {
+    let client_id = reader.u64()?;
+    let sequence = reader.u32()?;
+    reader.u32()?;
+    if reader.u32()? != SP4_NONE {
+        return Err(NfsError::Unsupported);
+    }
+    // Server owner, scope and implementation ids are not used
+    reader.u64()?;
+    reader.opaque(NFS4_OPAQUE_LIMIT)?;
+    reader.opaque(NFS4_OPAQUE_LIMIT)?;
+    let implementations = reader.u32()?;
+    if implementations > 1 {
+        return Err(NfsError::Malformed);
+    }
+    for _ in 0..implementations {
+        reader.opaque(NFS4_OPAQUE_LIMIT)?;
+        reader.opaque(NFS4_OPAQUE_LIMIT)?;
+        reader.u64()?;
+        reader.u32()?;
+    }
+    Ok((client_id, sequence))
+}
+
+pub fn decode_create_session(reader: &mut XdrReader) -> Result<SessionInfo, NfsError> {
+    let mut session = [0u8; NFS4_SESSIONID_SIZE];
+    session.copy_from_slice(reader.fixed(NFS4_SESSIONID_SIZE)?);
+    reader.u32()?;
+    reader.u32()?;
+    let (max_request, max_response) = decode_channel(reader)?;
+    decode_channel(reader)?;
+    Ok(SessionInfo { session, max_request, max_response })
+}
+
+pub fn decode_sequence(reader: &mut XdrReader) -> Result<(), NfsError> {
+    reader.fixed(NFS4_SESSIONID_SIZE)?;
+    for _ in 0..5 {
+        reader.u32()?;
+    }
+    Ok(())
+}
+
+pub fn decode_file_handle(reader: &mut XdrReader) -> Result<FileHandle, NfsError> {
+    Ok(FileHandle(reader.opaque(NFS4_FHSIZE)?.to_vec()))
+}
+
+/// A fattr4; attributes the client did not ask for cannot be skipped
+/// and fail the decoding
+pub fn decode_attributes(reader: &mut XdrReader) -> Result<Attributes, NfsError> {
+    let count = reader.u32()? as usize;
+    if count > MAX_BITMAP_WORDS {
+        return Err(NfsError::Malformed);
+    }
+    let mut bitmap = [0u32; MAX_BITMAP_WORDS];
+    for word in bitmap.iter_mut().take(count) {
+        *word = reader.u32()?;
+    }
+    let mut values = XdrReader::new(reader.opaque(MAX_ATTRIBUTES)?);
+    let mut attributes = Attributes::default();
+    for attribute in 0..(count * 32) as u32 {
+        if bitmap[attribute as usize / 32] & (1 << (attribute % 32)) == 0 {
+            continue;
+        }
+        match attribute {
+            ATTR_TYPE => attributes.file_type = FileType::from_raw(values.u32()?),
+            ATTR_SIZE => attributes.size = values.u64()?,
+            ATTR_FILEID => attributes.file_id = values.u64()?,
+            ATTR_MODE => attributes.mode = values.u32()?,
+            ATTR_TIME_MODIFY => {
+                attributes.modified = values.u64()? as i64;
+                values.u32()?;
+            }
+            _ => return Err(NfsError::Unsupported),
+        }
+    }
+    Ok(attributes)
+}
+
+/// Open stateid; a delegation would need a callback channel, so one
+/// granted despite the request is an error
+pub fn decode_open(reader: &mut XdrReader) -> Result<Stateid, NfsError> {
+    let stateid = decode_stateid(reader)?;
+    // Change info and result flags
+    reader.bool()?;
+    reader.u64()?;
+    reader.u64()?;
+    reader.u32()?;
+    skip_bitmap(reader)?;
+    match reader.u32()? {
+        OPEN_DELEGATE_NONE => {}
+        OPEN_DELEGATE_NONE_EXT => {
+            if matches!(reader.u32()?, WND4_CONTENTION | WND4_RESOURCE) {
+                reader.bool()?;
+            }
+        }
+        _ => return Err(NfsError::Unsupported),
+    }
+    Ok(stateid)
+}
+
+pub fn decode_close(reader: &mut XdrReader) -> Result<Stateid, NfsError> {
+    decode_stateid(reader)
+}
+
+/// Data read and whether it reaches the end of the file
+pub fn decode_read(reader: &mut XdrReader, max: usize) -> Result<(Vec<u8>, bool), NfsError> {
+    let eof = reader.bool()?;
+    Ok((reader.opaque(max)?.to_vec(), eof))
+}
+
+/// Bytes written, how stable they are and the write verifier
+pub fn decode_write(reader: &mut XdrReader) -> Result<(u32, u32, Verifier), NfsError> {
+    let count = reader.u32()?;
+    let committed = reader.u32()?;
+    Ok((count, committed, decode_verifier(reader)?))
+}
+
+pub fn decode_commit(reader: &mut XdrReader) -> Result<Verifier, NfsError> {
+    decode_verifier(reader)
+}
+
+/// Entries, the cookie verifier to continue with and whether the
+/// directory is complete
+pub fn decode_readdir(reader: &mut XdrReader) -> Result<(Vec<Entry>, Verifier, bool), NfsError> {
+    let verifier = decode_verifier(reader)?;
+    let mut entries = Vec::new();
+    while reader.bool()? {
+        let cookie = reader.u64()?;
+        let name = reader.string(MAX_NAME)?;
+        let attributes = decode_attributes(reader)?;
+        entries.push(Entry { cookie, name, attributes });
+    }
+    Ok((entries, verifier, reader.bool()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_attributes_in_bit_order() {
+        let mut values = XdrWriter::new();
+        values.u32(2).u64(4096).u64(77).u32(0o755).u64(1_700_000_000).u32(0);
+        let mut writer = XdrWriter::new();
+        encode_bitmap(&mut writer, &REQUESTED_ATTRIBUTES);
+        writer.opaque(&values.into_bytes());
+        let bytes = writer.into_bytes();
+        assert_eq!(bytes[..12], [0, 0, 0, 2, 0, 0x10, 0, 0x12, 0, 0x20, 0, 0x02]);
+
+        let attributes = decode_attributes(&mut XdrReader::new(&bytes)).unwrap();
+        let expected = Attributes {
+            file_type: FileType::Directory,
+            size: 4096,
+            file_id: 77,
+            mode: 0o755,
+            modified: 1_700_000_000,
+        };
+        assert_eq!(attributes, expected);
+
+        // An attribute that was not asked for
+        let mut writer = XdrWriter::new();
+        encode_bitmap(&mut writer, &[ATTR_TYPE, 2]);
+        writer.opaque(&[0, 0, 0, 1, 0, 0, 0, 1]);
+        assert_eq!(decode_attributes(&mut XdrReader::new(&writer.into_bytes())), Err(NfsError::Unsupported));
+    }
+}
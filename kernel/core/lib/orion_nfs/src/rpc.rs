@@ -0,0 +1,242 @@
+/*
+ * Orion Operating System - ONC RPC Client
+ *
+ * Calls of ONC RPC version 2 (RFC 5531) over a stream: every message is
+ * a record made of fragments, each behind a 4-byte marker whose top bit
+ * flags the last one. Calls carry AUTH_SYS credentials (or AUTH_NONE);
+ * replies are matched to the call by transaction id, and replies to
+ * calls given up on are skipped.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::xdr::{XdrReader, XdrWriter};
+use crate::NfsError;
+
+const RPC_VERSION: u32 = 2;
+const MSG_CALL: u32 = 0;
+const MSG_REPLY: u32 = 1;
+const MSG_ACCEPTED: u32 = 0;
+const ACCEPT_SUCCESS: u32 = 0;
+
+pub const AUTH_NONE: u32 = 0;
+pub const AUTH_SYS: u32 = 1;
+
+/// Longest credential or verifier body
+const MAX_AUTH_BODY: usize = 400;
+const MAX_MACHINE_NAME: usize = 255;
+const MAX_GROUPS: usize = 16;
+
+const LAST_FRAGMENT: u32 = 1 << 31;
+
+/// Largest record accepted from the server
+pub const MAX_RECORD: usize = 2 * 1024 * 1024;
+
+/// Bytes requested from the transport per read
+const READ_SIZE: usize = 8192;
+
+/// Byte stream to the server, usually a TCP connection
+pub trait RpcTransport {
+    /// Send all of `data`
+    fn send(&mut self, data: &[u8]) -> Result<(), NfsError>;
+    /// Read at least one byte into `buffer`, waiting for it if needed
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, NfsError>;
+}
+
+/// Credentials sent with every call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth {
+    None,
+    /// AUTH_SYS: the server trusts the identity the client states
+    Sys {
+        machine: String,
+        uid: u32,
+        gid: u32,
+        groups: Vec<u32>,
+    },
+}
+
+impl Auth {
+    fn encode(&self, writer: &mut XdrWriter) {
+        match self {
+            Auth::None => {
+                writer.u32(AUTH_NONE).u32(0);
+            }
+            Auth::Sys { machine, uid, gid, groups } => {
+                let mut body = XdrWriter::new();
+                body.u32(0).string(&machine[..machine.len().min(MAX_MACHINE_NAME)]).u32(*uid).u32(*gid);
+                let groups = &groups[..groups.len().min(MAX_GROUPS)];
+                body.u32(groups.len() as u32);
+                for group in groups {
+                    body.u32(*group);
+                }
+                writer.u32(AUTH_SYS).opaque(&body.into_bytes());
+            }
+        }
+    }
+}
+
+/// Split a record into one fragment
+pub fn frame(record: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + record.len());
+    out.extend_from_slice(&(LAST_FRAGMENT | record.len() as u32).to_be_bytes());
+    out.extend_from_slice(record);
+    out
+}
+
+/// The whole record at the head of `buffer` and the bytes it spans, or
+/// None while fragments are missing
+pub fn unframe(buffer: &[u8]) -> Result<Option<(Vec<u8>, usize)>, NfsError> {
+    let mut record = Vec::new();
+    let mut offset = 0;
+    loop {
+        let Some(marker) = buffer.get(offset..offset + 4) else {
+            return Ok(None);
+        };
+        let marker = u32::from_be_bytes([marker[0], marker[1], marker[2], marker[3]]);
+        let length = (marker & !LAST_FRAGMENT) as usize;
+        if record.len() + length > MAX_RECORD {
+            return Err(NfsError::Malformed);
+        }
+        let Some(fragment) = buffer.get(offset + 4..offset + 4 + length) else {
+            return Ok(None);
+        };
+        record.extend_from_slice(fragment);
+        offset += 4 + length;
+        if marker & LAST_FRAGMENT != 0 {
+            return Ok(Some((record, offset)));
+        }
+    }
+}
+
+pub struct RpcClient<T: RpcTransport> {
+    transport: T,
+    auth: Auth,
+    next_xid: u32,
+    inbound: Vec<u8>,
+}
+
+impl<T: RpcTransport> RpcClient<T> {
+    pub fn new(transport: T, auth: Auth, first_xid: u32) -> Self {
+        Self { transport, auth, next_xid: first_xid, inbound: Vec::new() }
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Call `procedure` and return the results of a successful reply
+    pub fn call(&mut self, program: u32, version: u32, procedure: u32, args: &[u8]) -> Result<Vec<u8>, NfsError> {
+        let xid = self.next_xid;
+        self.next_xid = self.next_xid.wrapping_add(1);
+
+        let mut call = XdrWriter::new();
+        call.u32(xid).u32(MSG_CALL).u32(RPC_VERSION).u32(program).u32(version).u32(procedure);
+        self.auth.encode(&mut call);
+        Auth::None.encode(&mut call);
+        call.raw(args);
+        self.transport.send(&frame(&call.into_bytes()))?;
+
+        loop {
+            let record = self.receive_record()?;
+            let mut reader = XdrReader::new(&record);
+            if reader.u32()? != xid {
+                continue;
+            }
+            if reader.u32()? != MSG_REPLY {
+                return Err(NfsError::Malformed);
+            }
+            if reader.u32()? != MSG_ACCEPTED {
+                return Err(NfsError::Rejected);
+            }
+            // Verifier of the server, not checked for AUTH_SYS
+            reader.u32()?;
+            reader.opaque(MAX_AUTH_BODY)?;
+            return match reader.u32()? {
+                ACCEPT_SUCCESS => Ok(reader.remaining().to_vec()),
+                status => Err(NfsError::Rpc(status)),
+            };
+        }
+    }
+
+    fn receive_record(&mut self) -> Result<Vec<u8>, NfsError> {
+        let mut buffer = [0u8; READ_SIZE];
+        loop {
+            if let Some((record, consumed)) = unframe(&self.inbound)? {
+                self.inbound.drain(..consumed);
+                return Ok(record);
+            }
+            let read = self.transport.receive(&mut buffer)?;
+            if read == 0 {
+                return Err(NfsError::Transport);
+            }
+            self.inbound.extend_from_slice(&buffer[..read]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Replies to every call with the queued records, in fragments
+    struct Replay {
+        sent: Vec<u8>,
+        replies: Vec<u8>,
+    }
+
+    impl RpcTransport for Replay {
+        fn send(&mut self, data: &[u8]) -> Result<(), NfsError> {
+            self.sent.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, NfsError> {
+            // Three bytes at a time to cross marker and fragment boundaries
+            let length = buffer.len().min(self.replies.len()).min(3);
+            buffer[..length].copy_from_slice(&self.replies[..length]);
+            self.replies.drain(..length);
+            Ok(length)
+        }
+    }
+
+    fn reply(xid: u32, accept: u32, results: &[u8]) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        writer.u32(xid).u32(MSG_REPLY).u32(MSG_ACCEPTED).u32(AUTH_NONE).u32(0).u32(accept).raw(results);
+        writer.into_bytes()
+    }
+
+    #[test]
+    fn test_matches_replies_to_calls() {
+        let auth = Auth::Sys { machine: String::from("orion"), uid: 1000, gid: 100, groups: vec![10] };
+        let mut replies = frame(&reply(6, ACCEPT_SUCCESS, &[0, 0, 0, 9]));
+        // Two fragments for the awaited reply
+        let second = reply(7, ACCEPT_SUCCESS, &[0, 0, 0, 1]);
+        replies.extend_from_slice(&(second.len() as u32 - 4).to_be_bytes());
+        replies.extend_from_slice(&second[..second.len() - 4]);
+        replies.extend_from_slice(&frame(&second[second.len() - 4..]));
+        let mut client = RpcClient::new(Replay { sent: Vec::new(), replies }, auth, 7);
+
+        assert_eq!(client.call(100003, 4, 1, &[0, 0, 0, 0]), Ok(vec![0, 0, 0, 1]));
+        let sent = core::mem::take(&mut client.transport().sent);
+        let (call, consumed) = unframe(&sent).unwrap().unwrap();
+        assert_eq!(consumed, sent.len());
+        let mut reader = XdrReader::new(&call);
+        let header: Vec<u32> = (0..6).map(|_| reader.u32().unwrap()).collect();
+        assert_eq!(header, [7, MSG_CALL, RPC_VERSION, 100003, 4, 1]);
+        assert_eq!(reader.u32(), Ok(AUTH_SYS));
+        let body = reader.opaque(MAX_AUTH_BODY).unwrap();
+        assert_eq!(body.len(), 4 + 4 + 8 + 4 + 4 + 4 + 4);
+
+        client.transport().replies = frame(&reply(8, 1, &[]));
+        assert_eq!(client.call(100003, 4, 1, &[]), Err(NfsError::Rpc(1)));
+    }
+}
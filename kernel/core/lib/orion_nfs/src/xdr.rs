@@ -0,0 +1,172 @@
+/*
+ * Orion Operating System - XDR Encoding
+ *
+ * External Data Representation (RFC 4506) as ONC RPC and NFS use it:
+ * big-endian 32-bit units, 64-bit hypers, and opaque data and strings
+ * carried with their length and padded to a multiple of four bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::NfsError;
+
+fn padding(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+#[derive(Debug, Default)]
+pub struct XdrWriter {
+    bytes: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.u32(value as u32)
+    }
+
+    /// Fixed-length opaque: the bytes and their padding
+    pub fn fixed(&mut self, data: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(data);
+        self.bytes.resize(self.bytes.len() + padding(data.len()), 0);
+        self
+    }
+
+    /// Variable-length opaque: length, bytes and padding
+    pub fn opaque(&mut self, data: &[u8]) -> &mut Self {
+        self.u32(data.len() as u32);
+        self.fixed(data)
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.opaque(value.as_bytes())
+    }
+
+    /// Bytes already in XDR form
+    pub fn raw(&mut self, data: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(data);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct XdrReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], NfsError> {
+        if self.bytes.len() < count {
+            return Err(NfsError::Malformed);
+        }
+        let (head, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, NfsError> {
+        self.take(4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, NfsError> {
+        let high = self.u32()? as u64;
+        Ok(high << 32 | self.u32()? as u64)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, NfsError> {
+        match self.u32()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(NfsError::Malformed),
+        }
+    }
+
+    pub fn fixed(&mut self, length: usize) -> Result<&'a [u8], NfsError> {
+        let data = self.take(length)?;
+        self.take(padding(length))?;
+        Ok(data)
+    }
+
+    /// Variable-length opaque of at most `max` bytes
+    pub fn opaque(&mut self, max: usize) -> Result<&'a [u8], NfsError> {
+        let length = self.u32()? as usize;
+        if length > max {
+            return Err(NfsError::Malformed);
+        }
+        self.fixed(length)
+    }
+
+    pub fn string(&mut self, max: usize) -> Result<String, NfsError> {
+        let bytes = self.opaque(max)?;
+        core::str::from_utf8(bytes).map(String::from).map_err(|_| NfsError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pads_opaque_data() {
+        let mut writer = XdrWriter::new();
+        writer.u32(7).string("abcde").u64(1 << 40).bool(true);
+        let bytes = writer.into_bytes();
+        assert_eq!(bytes.len(), 4 + 4 + 8 + 8 + 4);
+        assert_eq!(bytes[8..16], [b'a', b'b', b'c', b'd', b'e', 0, 0, 0]);
+
+        let mut reader = XdrReader::new(&bytes);
+        assert_eq!(reader.u32(), Ok(7));
+        assert_eq!(reader.string(4), Err(NfsError::Malformed));
+        let mut reader = XdrReader::new(&bytes[4..]);
+        assert_eq!(reader.string(255).as_deref(), Ok("abcde"));
+        assert_eq!(reader.u64(), Ok(1 << 40));
+        assert_eq!(reader.bool(), Ok(true));
+        assert!(reader.is_empty());
+        assert_eq!(reader.u32(), Err(NfsError::Malformed));
+    }
+}
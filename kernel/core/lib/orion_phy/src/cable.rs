@@ -0,0 +1,166 @@
+/*
+ * Orion Operating System - Cable Diagnostics
+ *
+ * Time domain reflectometry on the PHYs that have it: the PHY sends a
+ * pulse down each pair and times the reflection, which tells an intact
+ * pair from an open or shorted one and how far away the fault is. The
+ * link drops while the test runs.
+ *
+ * Only the Marvell virtual cable tester of the 88E1111 family is known
+ * here; other PHYs report Unsupported.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::mii::phy_id;
+use crate::{Mdio, PhyError};
+
+/// Twisted pairs of a 1000BASE-T cable
+pub const PAIRS: usize = 4;
+
+const PHY_ID_MODEL_MASK: u32 = 0xFFFF_FFF0;
+const PHY_ID_88E1111: u32 = 0x0141_0CC0;
+
+// Marvell registers
+const MARVELL_PAGE: u8 = 22;
+const MARVELL_VCT: u8 = 28;
+const VCT_RUN: u16 = 0x8000;
+const VCT_STATUS_SHIFT: u16 = 13;
+const VCT_STATUS_MASK: u16 = 0x3;
+const VCT_DISTANCE_MASK: u16 = 0x00FF;
+
+/// Register reads to wait for the tester, a few milliseconds of MDIO
+const VCT_POLLS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PairStatus {
+    #[default]
+    Ok,
+    Open,
+    Short,
+    /// The PHY could not tell, e.g. with a link partner transmitting
+    Failed,
+}
+
+impl PairStatus {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PairStatus::Ok),
+            1 => Some(PairStatus::Open),
+            2 => Some(PairStatus::Short),
+            3 => Some(PairStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PairResult {
+    pub status: PairStatus,
+    /// Distance to an open or a short
+    pub distance_m: Option<u16>,
+}
+
+/// Meters to the fault from the 88E1111 reflection time (application
+/// note formula: 0.8018 * reading - 28.751)
+fn marvell_distance(reading: u16) -> u16 {
+    ((reading as u32 * 8018).saturating_sub(287_510) / 10_000) as u16
+}
+
+fn marvell_pair(value: u16) -> PairResult {
+    let status = match (value >> VCT_STATUS_SHIFT) & VCT_STATUS_MASK {
+        0 => PairStatus::Ok,
+        1 => PairStatus::Short,
+        2 => PairStatus::Open,
+        _ => PairStatus::Failed,
+    };
+    let distance_m = match status {
+        PairStatus::Open | PairStatus::Short => Some(marvell_distance(value & VCT_DISTANCE_MASK)),
+        _ => None,
+    };
+    PairResult { status, distance_m }
+}
+
+fn marvell_vct(mdio: &mut dyn Mdio) -> Result<[PairResult; PAIRS], PhyError> {
+    mdio.write(MARVELL_PAGE, 0)?;
+    mdio.write(MARVELL_VCT, VCT_RUN)?;
+    let mut running = true;
+    for _ in 0..VCT_POLLS {
+        if mdio.read(MARVELL_VCT)? & VCT_RUN == 0 {
+            running = false;
+            break;
+        }
+    }
+    if running {
+        return Err(PhyError::Timeout);
+    }
+
+    // Page n of the VCT register holds the result of pair n
+    let mut pairs = [PairResult::default(); PAIRS];
+    for (pair, result) in pairs.iter_mut().enumerate() {
+        mdio.write(MARVELL_PAGE, pair as u16)?;
+        *result = marvell_pair(mdio.read(MARVELL_VCT)?);
+    }
+    Ok(pairs)
+}
+
+/// Test the four pairs of the cable
+pub fn cable_test(mdio: &mut dyn Mdio) -> Result<[PairResult; PAIRS], PhyError> {
+    if phy_id(mdio)? & PHY_ID_MODEL_MASK != PHY_ID_88E1111 {
+        return Err(PhyError::Unsupported);
+    }
+    let result = marvell_vct(mdio);
+    // Leave the PHY on its first page whatever happened
+    mdio.write(MARVELL_PAGE, 0)?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mii::tests::FakePhy;
+
+    /// 88E1111 with an open pair 2 and a short on pair 4
+    struct Marvell {
+        phy: FakePhy,
+        results: [u16; PAIRS],
+    }
+
+    impl Mdio for Marvell {
+        fn read(&mut self, register: u8) -> Result<u16, PhyError> {
+            match register {
+                MARVELL_VCT => Ok(self.results[self.phy.registers[MARVELL_PAGE as usize] as usize]),
+                _ => self.phy.read(register),
+            }
+        }
+
+        fn write(&mut self, register: u8, value: u16) -> Result<(), PhyError> {
+            if register == MARVELL_VCT {
+                return Ok(());
+            }
+            self.phy.write(register, value)
+        }
+    }
+
+    #[test]
+    fn test_reports_faults_per_pair() {
+        let mut marvell = Marvell { phy: FakePhy::new(), results: [0, 0x4000 | 100, 0, 0x2000 | 40] };
+        let pairs = cable_test(&mut marvell).unwrap();
+        assert_eq!(pairs[0], PairResult { status: PairStatus::Ok, distance_m: None });
+        assert_eq!(pairs[1], PairResult { status: PairStatus::Open, distance_m: Some(51) });
+        assert_eq!(pairs[3], PairResult { status: PairStatus::Short, distance_m: Some(3) });
+        assert_eq!(marvell.phy.registers[MARVELL_PAGE as usize], 0);
+
+        let mut other = FakePhy::new();
+        other.registers[3] = 0x0390;
+        assert_eq!(cable_test(&mut other), Err(PhyError::Unsupported));
+    }
+}
@@ -0,0 +1,163 @@
+/*
+ * Orion Operating System - PHY Control Client
+ *
+ * Client side of the PHY control requests. Errors are the negative
+ * errno of the reply, or EIO when the driver did not answer.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::cable::{PairResult, PairStatus, PAIRS};
+use crate::control::{
+    read_u32, CTRL_AUTONEG, CTRL_CABLE_TEST, CTRL_FORCE, CTRL_READ, CTRL_STATUS, CTRL_WRITE, NO_DISTANCE, PAIR_SIZE,
+    STATUS_EIO, STATUS_OK, STATUS_SIZE,
+};
+use crate::mii::{Abilities, LinkMode};
+
+/// Carries a control request to a driver and returns its reply
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhyStatus {
+    /// PHYSID1:PHYSID2, 0 when the MAC does not map them
+    pub id: u32,
+    pub supported: Abilities,
+    pub advertised: Abilities,
+    /// Empty until auto-negotiation completes
+    pub partner: Abilities,
+    pub autoneg: bool,
+    /// None while the link is down
+    pub link: Option<LinkMode>,
+    pub bmcr: u16,
+    pub bmsr: u16,
+}
+
+pub struct PhyClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> PhyClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, opcode: u32, arguments: &[u32]) -> Result<Vec<u8>, i32> {
+        let mut request = Vec::with_capacity(4 + arguments.len() * 4);
+        request.extend_from_slice(&opcode.to_le_bytes());
+        for argument in arguments {
+            request.extend_from_slice(&argument.to_le_bytes());
+        }
+        let response = self.transport.call(&request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    pub fn status(&mut self) -> Result<PhyStatus, i32> {
+        let payload = self.call(CTRL_STATUS, &[])?;
+        if payload.len() < STATUS_SIZE {
+            return Err(STATUS_EIO);
+        }
+        let word = |offset: usize| read_u32(&payload, offset).unwrap_or(0);
+        let half = |offset: usize| u16::from_le_bytes([payload[offset], payload[offset + 1]]);
+        let speed_mbps = word(16);
+        Ok(PhyStatus {
+            id: word(0),
+            supported: Abilities(word(4)),
+            advertised: Abilities(word(8)),
+            partner: Abilities(word(12)),
+            autoneg: payload[20] != 0,
+            link: (speed_mbps != 0).then_some(LinkMode { speed_mbps, full_duplex: payload[21] != 0 }),
+            bmcr: half(22),
+            bmsr: half(24),
+        })
+    }
+
+    pub fn read(&mut self, register: u8) -> Result<u16, i32> {
+        let payload = self.call(CTRL_READ, &[register as u32])?;
+        match payload.get(..2) {
+            Some(value) => Ok(u16::from_le_bytes([value[0], value[1]])),
+            None => Err(STATUS_EIO),
+        }
+    }
+
+    pub fn write(&mut self, register: u8, value: u16) -> Result<(), i32> {
+        self.call(CTRL_WRITE, &[register as u32, value as u32]).map(|_| ())
+    }
+
+    /// Restart auto-negotiation advertising `advertise`; with no mode in
+    /// it, every mode the PHY has
+    pub fn autoneg(&mut self, advertise: Abilities) -> Result<(), i32> {
+        self.call(CTRL_AUTONEG, &[advertise.0]).map(|_| ())
+    }
+
+    pub fn force(&mut self, mode: LinkMode) -> Result<(), i32> {
+        self.call(CTRL_FORCE, &[mode.speed_mbps, mode.full_duplex as u32]).map(|_| ())
+    }
+
+    /// Run the cable test; the link goes down while it runs
+    pub fn cable_test(&mut self) -> Result<[PairResult; PAIRS], i32> {
+        let payload = self.call(CTRL_CABLE_TEST, &[])?;
+        if payload.len() < PAIRS * PAIR_SIZE {
+            return Err(STATUS_EIO);
+        }
+        let mut pairs = [PairResult::default(); PAIRS];
+        for (result, record) in pairs.iter_mut().zip(payload.chunks_exact(PAIR_SIZE)) {
+            let distance = u16::from_le_bytes([record[2], record[3]]);
+            *result = PairResult {
+                status: PairStatus::from_u8(record[0]).ok_or(STATUS_EIO)?,
+                distance_m: (distance != NO_DISTANCE).then_some(distance),
+            };
+        }
+        Ok(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{handle_control, STATUS_EINVAL, STATUS_EOPNOTSUPP};
+    use crate::mii::tests::FakePhy;
+    use crate::mii::MII_ADVERTISE;
+
+    impl Transport for &mut FakePhy {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            Some(handle_control(*self, request))
+        }
+    }
+
+    #[test]
+    fn test_manages_the_phy_through_requests() {
+        let mut phy = FakePhy::new();
+        // A PHY without a cable tester
+        phy.registers[3] = 0x0390;
+        let mut client = PhyClient::new(&mut phy);
+
+        let status = client.status().unwrap();
+        assert_eq!(status.id, 0x0141_0390);
+        assert!(status.autoneg);
+        assert_eq!(status.link, Some(LinkMode { speed_mbps: 1000, full_duplex: true }));
+        assert!(status.partner.contains(Abilities::HUNDRED_FULL | Abilities::AUTONEG));
+
+        assert_eq!(client.autoneg(Abilities(Abilities::TEN_FULL)), Ok(()));
+        assert_eq!(client.read(MII_ADVERTISE), Ok(0x0041));
+        // Every mode, keeping the pause bits (none after the last call)
+        assert_eq!(client.autoneg(Abilities::default()), Ok(()));
+        assert_eq!(client.read(MII_ADVERTISE), Ok(0x01E1));
+
+        assert_eq!(client.force(LinkMode { speed_mbps: 10, full_duplex: true }), Ok(()));
+        assert!(!client.status().unwrap().autoneg);
+        assert_eq!(client.write(40, 1), Err(STATUS_EINVAL));
+        assert_eq!(client.cable_test(), Err(STATUS_EOPNOTSUPP));
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * Orion Operating System - PHY Control Requests
+ *
+ * Requests a NIC driver accepts as an ioctl of length PHY_IOCTL_CONTROL.
+ * Little-endian; every request starts with a u32 opcode and every reply
+ * with an i32 status (0 or a negative errno).
+ *
+ *   STATUS                           -> id:u32 supported:u32
+ *                                       advertised:u32 partner:u32
+ *                                       speed_mbps:u32 autoneg:u8
+ *                                       full_duplex:u8 bmcr:u16 bmsr:u16
+ *                                       reserved:u16
+ *   READ        register:u32         -> value:u16
+ *   WRITE       register:u32 value:u32 ->
+ *   AUTONEG     advertise:u32        ->
+ *   FORCE       speed_mbps:u32 full_duplex:u32 ->
+ *   CABLE_TEST                       -> {status:u8 reserved:u8 distance_m:u16} x 4
+ *
+ * Abilities are Abilities bits. speed_mbps is 0 while the link is down.
+ * AUTONEG with no mode advertises every mode the PHY has and keeps its
+ * pause bits. A distance of 0xFFFF means none was measured.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::cable::cable_test;
+use crate::mii::{self, Abilities, LinkMode, MII_BMCR, MII_BMSR, MII_REGISTERS};
+use crate::{Mdio, PhyError};
+
+/// Ioctl carrying a PHY control request; the reply is sent back as data
+pub const PHY_IOCTL_CONTROL: u32 = 0x2030;
+
+// Opcodes
+pub const CTRL_STATUS: u32 = 1;
+pub const CTRL_READ: u32 = 2;
+pub const CTRL_WRITE: u32 = 3;
+pub const CTRL_AUTONEG: u32 = 4;
+pub const CTRL_FORCE: u32 = 5;
+pub const CTRL_CABLE_TEST: u32 = 6;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_ENXIO: i32 = -6;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_EOPNOTSUPP: i32 = -95;
+pub const STATUS_ETIMEDOUT: i32 = -110;
+
+/// Size of the STATUS payload
+pub const STATUS_SIZE: usize = 28;
+/// Size of one pair in the CABLE_TEST payload
+pub const PAIR_SIZE: usize = 4;
+/// Distance of a pair without a fault
+pub const NO_DISTANCE: u16 = 0xFFFF;
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn error_status(error: PhyError) -> i32 {
+    match error {
+        PhyError::InvalidArgument => STATUS_EINVAL,
+        PhyError::Unsupported => STATUS_EOPNOTSUPP,
+        PhyError::NoRegister => STATUS_ENXIO,
+        PhyError::Bus => STATUS_EIO,
+        PhyError::Timeout => STATUS_ETIMEDOUT,
+    }
+}
+
+fn status(mdio: &mut dyn Mdio) -> Result<Vec<u8>, PhyError> {
+    // Some MACs only map the basic registers and have no identifier
+    let id = match mii::phy_id(mdio) {
+        Err(PhyError::NoRegister) => 0,
+        id => id?,
+    };
+    let supported = mii::supported(mdio)?;
+    let advertised = mii::advertised(mdio, supported)?;
+    let partner = mii::partner(mdio, supported)?;
+    let mode = mii::link_mode(mdio)?;
+    let bmcr = mdio.read(MII_BMCR)?;
+    let bmsr = mdio.read(MII_BMSR)?;
+
+    let mut out = Vec::with_capacity(STATUS_SIZE);
+    for value in [id, supported.0, advertised.0, partner.0, mode.map_or(0, |mode| mode.speed_mbps)] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.push(mii::autoneg_enabled(mdio)? as u8);
+    out.push(mode.is_some_and(|mode| mode.full_duplex) as u8);
+    out.extend_from_slice(&bmcr.to_le_bytes());
+    out.extend_from_slice(&bmsr.to_le_bytes());
+    out.extend_from_slice(&[0, 0]);
+    Ok(out)
+}
+
+fn register(request: &[u8]) -> Result<u8, PhyError> {
+    match read_u32(request, 4) {
+        Some(register) if register < MII_REGISTERS as u32 => Ok(register as u8),
+        _ => Err(PhyError::InvalidArgument),
+    }
+}
+
+fn autoneg(mdio: &mut dyn Mdio, request: &[u8]) -> Result<(), PhyError> {
+    let mut advertise = Abilities(read_u32(request, 4).ok_or(PhyError::InvalidArgument)?);
+    if advertise.modes().0 == 0 {
+        let supported = mii::supported(mdio)?;
+        let pause = mii::advertised(mdio, supported)?.0 & (Abilities::PAUSE | Abilities::ASYM_PAUSE);
+        advertise = Abilities(supported.modes().0 | pause | advertise.0);
+    }
+    mii::restart_autoneg(mdio, advertise)
+}
+
+fn control(mdio: &mut dyn Mdio, request: &[u8]) -> Result<Vec<u8>, PhyError> {
+    match read_u32(request, 0).ok_or(PhyError::InvalidArgument)? {
+        CTRL_STATUS => status(mdio),
+        CTRL_READ => Ok(mdio.read(register(request)?)?.to_le_bytes().to_vec()),
+        CTRL_WRITE => {
+            let value = read_u32(request, 8).filter(|&value| value <= 0xFFFF).ok_or(PhyError::InvalidArgument)?;
+            mdio.write(register(request)?, value as u16).map(|_| Vec::new())
+        }
+        CTRL_AUTONEG => autoneg(mdio, request).map(|_| Vec::new()),
+        CTRL_FORCE => match (read_u32(request, 4), read_u32(request, 8)) {
+            (Some(speed_mbps), Some(full_duplex)) => {
+                mii::force_mode(mdio, LinkMode { speed_mbps, full_duplex: full_duplex != 0 }).map(|_| Vec::new())
+            }
+            _ => Err(PhyError::InvalidArgument),
+        },
+        CTRL_CABLE_TEST => {
+            let pairs = cable_test(mdio)?;
+            let mut out = Vec::with_capacity(pairs.len() * PAIR_SIZE);
+            for pair in pairs {
+                out.extend_from_slice(&[pair.status.as_u8(), 0]);
+                out.extend_from_slice(&pair.distance_m.unwrap_or(NO_DISTANCE).to_le_bytes());
+            }
+            Ok(out)
+        }
+        _ => Err(PhyError::Unsupported),
+    }
+}
+
+/// Serve one control request on the PHY behind `mdio`. Drivers check
+/// their link again after AUTONEG, FORCE and CABLE_TEST, which change it.
+pub fn handle_control(mdio: &mut dyn Mdio, request: &[u8]) -> Vec<u8> {
+    let (status, payload) = match control(mdio, request) {
+        Ok(payload) => (STATUS_OK, payload),
+        Err(error) => (error_status(error), Vec::new()),
+    };
+    let mut out = Vec::with_capacity(4 + payload.len());
+    out.extend_from_slice(&status.to_le_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
@@ -0,0 +1,52 @@
+/*
+ * Orion Operating System - Ethernet PHY Management
+ *
+ * What NIC drivers share about their PHY: clause 22 register access over
+ * the MAC's MDIO interface, the abilities both ends of a link advertise,
+ * restarting auto-negotiation or forcing a speed and duplex, and cable
+ * diagnostics on the PHYs that can measure their pairs.
+ *
+ * A driver implements Mdio for its MDIO interface and passes its
+ * PHY_IOCTL_CONTROL requests to handle_control; management tools use
+ * PhyClient.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod cable;
+pub mod client;
+pub mod control;
+pub mod mii;
+
+pub use cable::{cable_test, PairResult, PairStatus};
+pub use client::{PhyClient, PhyStatus, Transport};
+pub use control::{handle_control, PHY_IOCTL_CONTROL};
+pub use mii::{Abilities, LinkMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhyError {
+    InvalidArgument,
+    /// The PHY cannot do it (a mode it lacks, no cable tester)
+    Unsupported,
+    /// The register is not reachable through this MDIO interface
+    NoRegister,
+    /// The MDIO access failed or timed out
+    Bus,
+    /// A cable test did not finish
+    Timeout,
+}
+
+/// Clause 22 register access to the PHY of one NIC
+pub trait Mdio {
+    fn read(&mut self, register: u8) -> Result<u16, PhyError>;
+
+    fn write(&mut self, register: u8, value: u16) -> Result<(), PhyError>;
+}
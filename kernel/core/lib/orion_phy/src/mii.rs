@@ -0,0 +1,392 @@
+/*
+ * Orion Operating System - MII Registers and Link Negotiation
+ *
+ * The IEEE 802.3 clause 22 registers every 10/100/1000BASE-T PHY has,
+ * and what the drivers do with them: read the abilities of each end,
+ * resolve the mode a negotiated link runs at, restart auto-negotiation
+ * with a given advertisement or force a speed and duplex.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::{Mdio, PhyError};
+
+// Registers
+pub const MII_BMCR: u8 = 0;
+pub const MII_BMSR: u8 = 1;
+pub const MII_PHYSID1: u8 = 2;
+pub const MII_PHYSID2: u8 = 3;
+pub const MII_ADVERTISE: u8 = 4;
+pub const MII_LPA: u8 = 5;
+pub const MII_EXPANSION: u8 = 6;
+pub const MII_CTRL1000: u8 = 9;
+pub const MII_STAT1000: u8 = 10;
+pub const MII_ESTATUS: u8 = 15;
+/// Clause 22 has 32 registers per PHY
+pub const MII_REGISTERS: u8 = 32;
+
+// BMCR
+pub const BMCR_SPEED1000: u16 = 0x0040;
+pub const BMCR_FULLDPLX: u16 = 0x0100;
+pub const BMCR_ANRESTART: u16 = 0x0200;
+pub const BMCR_ISOLATE: u16 = 0x0400;
+pub const BMCR_PDOWN: u16 = 0x0800;
+pub const BMCR_ANENABLE: u16 = 0x1000;
+pub const BMCR_SPEED100: u16 = 0x2000;
+pub const BMCR_LOOPBACK: u16 = 0x4000;
+
+// BMSR
+pub const BMSR_LSTATUS: u16 = 0x0004;
+pub const BMSR_ANEGCAPABLE: u16 = 0x0008;
+pub const BMSR_ANEGCOMPLETE: u16 = 0x0020;
+pub const BMSR_ESTATEN: u16 = 0x0100;
+pub const BMSR_10HALF: u16 = 0x0800;
+pub const BMSR_10FULL: u16 = 0x1000;
+pub const BMSR_100HALF: u16 = 0x2000;
+pub const BMSR_100FULL: u16 = 0x4000;
+
+// ADVERTISE and LPA
+pub const ADVERTISE_CSMA: u16 = 0x0001;
+pub const ADVERTISE_10HALF: u16 = 0x0020;
+pub const ADVERTISE_10FULL: u16 = 0x0040;
+pub const ADVERTISE_100HALF: u16 = 0x0080;
+pub const ADVERTISE_100FULL: u16 = 0x0100;
+pub const ADVERTISE_PAUSE: u16 = 0x0400;
+pub const ADVERTISE_ASYM_PAUSE: u16 = 0x0800;
+const ADVERTISE_ALL: u16 = ADVERTISE_10HALF
+    | ADVERTISE_10FULL
+    | ADVERTISE_100HALF
+    | ADVERTISE_100FULL
+    | ADVERTISE_PAUSE
+    | ADVERTISE_ASYM_PAUSE;
+
+// EXPANSION
+pub const EXPANSION_LP_ANEG: u16 = 0x0001;
+
+// CTRL1000, STAT1000 and ESTATUS
+pub const ADVERTISE_1000HALF: u16 = 0x0100;
+pub const ADVERTISE_1000FULL: u16 = 0x0200;
+pub const LPA_1000HALF: u16 = 0x0400;
+pub const LPA_1000FULL: u16 = 0x0800;
+pub const ESTATUS_1000T_HALF: u16 = 0x1000;
+pub const ESTATUS_1000T_FULL: u16 = 0x2000;
+
+/// Link modes and negotiation abilities of one end of a link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Abilities(pub u32);
+
+impl Abilities {
+    pub const TEN_HALF: u32 = 1 << 0;
+    pub const TEN_FULL: u32 = 1 << 1;
+    pub const HUNDRED_HALF: u32 = 1 << 2;
+    pub const HUNDRED_FULL: u32 = 1 << 3;
+    pub const GIGABIT_HALF: u32 = 1 << 4;
+    pub const GIGABIT_FULL: u32 = 1 << 5;
+    pub const PAUSE: u32 = 1 << 6;
+    pub const ASYM_PAUSE: u32 = 1 << 7;
+    pub const AUTONEG: u32 = 1 << 8;
+
+    /// The speed and duplex modes, without pause and auto-negotiation
+    pub const MODES: u32 = 0x3F;
+    pub const GIGABIT: u32 = Self::GIGABIT_HALF | Self::GIGABIT_FULL;
+
+    /// Modes from the fastest down, the order negotiation picks them in
+    const PRIORITY: [(u32, LinkMode); 6] = [
+        (Self::GIGABIT_FULL, LinkMode { speed_mbps: 1000, full_duplex: true }),
+        (Self::GIGABIT_HALF, LinkMode { speed_mbps: 1000, full_duplex: false }),
+        (Self::HUNDRED_FULL, LinkMode { speed_mbps: 100, full_duplex: true }),
+        (Self::HUNDRED_HALF, LinkMode { speed_mbps: 100, full_duplex: false }),
+        (Self::TEN_FULL, LinkMode { speed_mbps: 10, full_duplex: true }),
+        (Self::TEN_HALF, LinkMode { speed_mbps: 10, full_duplex: false }),
+    ];
+
+    pub fn contains(self, bits: u32) -> bool {
+        self.0 & bits == bits
+    }
+
+    pub fn modes(self) -> Self {
+        Abilities(self.0 & Self::MODES)
+    }
+
+    /// Best mode of the set
+    pub fn best(self) -> Option<LinkMode> {
+        Self::PRIORITY.iter().find(|(bit, _)| self.0 & bit != 0).map(|&(_, mode)| mode)
+    }
+
+    /// Abilities in ADVERTISE or LPA
+    fn from_advertise(value: u16) -> Self {
+        let bits = [
+            (ADVERTISE_10HALF, Self::TEN_HALF),
+            (ADVERTISE_10FULL, Self::TEN_FULL),
+            (ADVERTISE_100HALF, Self::HUNDRED_HALF),
+            (ADVERTISE_100FULL, Self::HUNDRED_FULL),
+            (ADVERTISE_PAUSE, Self::PAUSE),
+            (ADVERTISE_ASYM_PAUSE, Self::ASYM_PAUSE),
+        ];
+        Abilities(bits.iter().filter(|(register, _)| value & register != 0).fold(0, |set, (_, bit)| set | bit))
+    }
+
+    fn to_advertise(self) -> u16 {
+        let bits = [
+            (Self::TEN_HALF, ADVERTISE_10HALF),
+            (Self::TEN_FULL, ADVERTISE_10FULL),
+            (Self::HUNDRED_HALF, ADVERTISE_100HALF),
+            (Self::HUNDRED_FULL, ADVERTISE_100FULL),
+            (Self::PAUSE, ADVERTISE_PAUSE),
+            (Self::ASYM_PAUSE, ADVERTISE_ASYM_PAUSE),
+        ];
+        bits.iter().filter(|(bit, _)| self.0 & bit != 0).fold(0, |value, (_, register)| value | register)
+    }
+}
+
+/// Speed and duplex a link runs at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkMode {
+    pub speed_mbps: u32,
+    pub full_duplex: bool,
+}
+
+impl LinkMode {
+    /// The ability bit of the mode, None for a speed clause 22 has no mode for
+    pub fn ability(self) -> Option<u32> {
+        Abilities::PRIORITY.iter().find(|(_, mode)| *mode == self).map(|&(bit, _)| bit)
+    }
+}
+
+/// PHY identifier: OUI, model and revision from PHYSID1 and PHYSID2
+pub fn phy_id(mdio: &mut dyn Mdio) -> Result<u32, PhyError> {
+    Ok((mdio.read(MII_PHYSID1)? as u32) << 16 | mdio.read(MII_PHYSID2)? as u32)
+}
+
+/// Modes the PHY can run at
+pub fn supported(mdio: &mut dyn Mdio) -> Result<Abilities, PhyError> {
+    let bmsr = mdio.read(MII_BMSR)?;
+    let bits = [
+        (BMSR_10HALF, Abilities::TEN_HALF),
+        (BMSR_10FULL, Abilities::TEN_FULL),
+        (BMSR_100HALF, Abilities::HUNDRED_HALF),
+        (BMSR_100FULL, Abilities::HUNDRED_FULL),
+        (BMSR_ANEGCAPABLE, Abilities::AUTONEG),
+    ];
+    let mut set = bits.iter().filter(|(register, _)| bmsr & register != 0).fold(0, |set, (_, bit)| set | bit);
+    if bmsr & BMSR_ESTATEN != 0 {
+        let estatus = mdio.read(MII_ESTATUS)?;
+        if estatus & ESTATUS_1000T_HALF != 0 {
+            set |= Abilities::GIGABIT_HALF;
+        }
+        if estatus & ESTATUS_1000T_FULL != 0 {
+            set |= Abilities::GIGABIT_FULL;
+        }
+    }
+    Ok(Abilities(set))
+}
+
+/// What the PHY advertises to its link partner
+pub fn advertised(mdio: &mut dyn Mdio, supported: Abilities) -> Result<Abilities, PhyError> {
+    let mut set = Abilities::from_advertise(mdio.read(MII_ADVERTISE)?).0;
+    if supported.0 & Abilities::GIGABIT != 0 {
+        let ctrl1000 = mdio.read(MII_CTRL1000)?;
+        if ctrl1000 & ADVERTISE_1000HALF != 0 {
+            set |= Abilities::GIGABIT_HALF;
+        }
+        if ctrl1000 & ADVERTISE_1000FULL != 0 {
+            set |= Abilities::GIGABIT_FULL;
+        }
+    }
+    Ok(Abilities(set))
+}
+
+/// What the link partner advertised, empty until auto-negotiation completes
+pub fn partner(mdio: &mut dyn Mdio, supported: Abilities) -> Result<Abilities, PhyError> {
+    if mdio.read(MII_BMSR)? & BMSR_ANEGCOMPLETE == 0 {
+        return Ok(Abilities::default());
+    }
+    let mut set = Abilities::from_advertise(mdio.read(MII_LPA)?).0;
+    if supported.0 & Abilities::GIGABIT != 0 {
+        let stat1000 = mdio.read(MII_STAT1000)?;
+        if stat1000 & LPA_1000HALF != 0 {
+            set |= Abilities::GIGABIT_HALF;
+        }
+        if stat1000 & LPA_1000FULL != 0 {
+            set |= Abilities::GIGABIT_FULL;
+        }
+    }
+    if mdio.read(MII_EXPANSION)? & EXPANSION_LP_ANEG != 0 {
+        set |= Abilities::AUTONEG;
+    }
+    Ok(Abilities(set))
+}
+
+pub fn autoneg_enabled(mdio: &mut dyn Mdio) -> Result<bool, PhyError> {
+    Ok(mdio.read(MII_BMCR)? & BMCR_ANENABLE != 0)
+}
+
+/// Mode the link runs at, None while it is down or still negotiating
+pub fn link_mode(mdio: &mut dyn Mdio) -> Result<Option<LinkMode>, PhyError> {
+    // The link bit latches a loss until read; the second read is current
+    mdio.read(MII_BMSR)?;
+    let bmsr = mdio.read(MII_BMSR)?;
+    if bmsr & BMSR_LSTATUS == 0 {
+        return Ok(None);
+    }
+    let bmcr = mdio.read(MII_BMCR)?;
+    if bmcr & BMCR_ANENABLE == 0 {
+        let speed_mbps = match bmcr & (BMCR_SPEED1000 | BMCR_SPEED100) {
+            BMCR_SPEED1000 => 1000,
+            BMCR_SPEED100 => 100,
+            _ => 10,
+        };
+        return Ok(Some(LinkMode { speed_mbps, full_duplex: bmcr & BMCR_FULLDPLX != 0 }));
+    }
+    let supported = supported(mdio)?;
+    let common = advertised(mdio, supported)?.0 & partner(mdio, supported)?.0;
+    Ok(Abilities(common).best())
+}
+
+/// Advertise the modes of `advertise` the PHY has, with its pause bits,
+/// and restart auto-negotiation
+pub fn restart_autoneg(mdio: &mut dyn Mdio, advertise: Abilities) -> Result<(), PhyError> {
+    let supported = supported(mdio)?;
+    if !supported.contains(Abilities::AUTONEG) {
+        return Err(PhyError::Unsupported);
+    }
+    let modes = advertise.0 & supported.0 & Abilities::MODES;
+    if modes == 0 {
+        return Err(PhyError::InvalidArgument);
+    }
+    let wanted = Abilities(modes | (advertise.0 & (Abilities::PAUSE | Abilities::ASYM_PAUSE)));
+
+    let anar = mdio.read(MII_ADVERTISE)? & !ADVERTISE_ALL;
+    mdio.write(MII_ADVERTISE, anar | ADVERTISE_CSMA | wanted.to_advertise())?;
+    if supported.0 & Abilities::GIGABIT != 0 {
+        let mut ctrl1000 = mdio.read(MII_CTRL1000)? & !(ADVERTISE_1000HALF | ADVERTISE_1000FULL);
+        if modes & Abilities::GIGABIT_HALF != 0 {
+            ctrl1000 |= ADVERTISE_1000HALF;
+        }
+        if modes & Abilities::GIGABIT_FULL != 0 {
+            ctrl1000 |= ADVERTISE_1000FULL;
+        }
+        mdio.write(MII_CTRL1000, ctrl1000)?;
+    }
+    let bmcr = mdio.read(MII_BMCR)? & !(BMCR_ISOLATE | BMCR_PDOWN | BMCR_LOOPBACK);
+    mdio.write(MII_BMCR, bmcr | BMCR_ANENABLE | BMCR_ANRESTART)
+}
+
+/// Turn auto-negotiation off and run at `mode`. 1000BASE-T cannot be
+/// forced: its master/slave roles are settled by negotiation.
+pub fn force_mode(mdio: &mut dyn Mdio, mode: LinkMode) -> Result<(), PhyError> {
+    let bit = mode.ability().ok_or(PhyError::InvalidArgument)?;
+    if bit & Abilities::GIGABIT != 0 {
+        return Err(PhyError::InvalidArgument);
+    }
+    if !supported(mdio)?.contains(bit) {
+        return Err(PhyError::Unsupported);
+    }
+    let cleared = BMCR_ANENABLE
+        | BMCR_ANRESTART
+        | BMCR_SPEED1000
+        | BMCR_SPEED100
+        | BMCR_FULLDPLX
+        | BMCR_ISOLATE
+        | BMCR_PDOWN
+        | BMCR_LOOPBACK;
+    let mut bmcr = mdio.read(MII_BMCR)? & !cleared;
+    if mode.speed_mbps == 100 {
+        bmcr |= BMCR_SPEED100;
+    }
+    if mode.full_duplex {
+        bmcr |= BMCR_FULLDPLX;
+    }
+    mdio.write(MII_BMCR, bmcr)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Gigabit PHY whose partner advertises 100 full and 1000 full
+    pub struct FakePhy {
+        pub registers: [u16; 32],
+    }
+
+    impl FakePhy {
+        pub fn new() -> Self {
+            let mut registers = [0u16; 32];
+            registers[MII_BMCR as usize] = BMCR_ANENABLE | BMCR_SPEED1000 | BMCR_FULLDPLX;
+            registers[MII_BMSR as usize] = BMSR_10HALF
+                | BMSR_10FULL
+                | BMSR_100HALF
+                | BMSR_100FULL
+                | BMSR_ESTATEN
+                | BMSR_ANEGCAPABLE
+                | BMSR_ANEGCOMPLETE
+                | BMSR_LSTATUS;
+            registers[MII_PHYSID1 as usize] = 0x0141;
+            registers[MII_PHYSID2 as usize] = 0x0CC2;
+            registers[MII_ADVERTISE as usize] = ADVERTISE_CSMA | ADVERTISE_ALL;
+            registers[MII_LPA as usize] = ADVERTISE_100FULL | ADVERTISE_PAUSE;
+            registers[MII_EXPANSION as usize] = EXPANSION_LP_ANEG;
+            registers[MII_CTRL1000 as usize] = ADVERTISE_1000FULL;
+            registers[MII_STAT1000 as usize] = LPA_1000FULL;
+            registers[MII_ESTATUS as usize] = ESTATUS_1000T_FULL;
+            Self { registers }
+        }
+    }
+
+    impl Mdio for FakePhy {
+        fn read(&mut self, register: u8) -> Result<u16, PhyError> {
+            self.registers.get(register as usize).copied().ok_or(PhyError::NoRegister)
+        }
+
+        fn write(&mut self, register: u8, value: u16) -> Result<(), PhyError> {
+            *self.registers.get_mut(register as usize).ok_or(PhyError::NoRegister)? = value;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_resolves_the_negotiated_mode() {
+        let mut phy = FakePhy::new();
+        let gigabit = LinkMode { speed_mbps: 1000, full_duplex: true };
+        assert_eq!(link_mode(&mut phy), Ok(Some(gigabit)));
+
+        let supported = supported(&mut phy).unwrap();
+        assert!(supported.contains(Abilities::GIGABIT_FULL | Abilities::AUTONEG));
+        assert!(!supported.contains(Abilities::GIGABIT_HALF));
+        let partner = partner(&mut phy, supported).unwrap();
+        assert_eq!(
+            partner,
+            Abilities(Abilities::HUNDRED_FULL | Abilities::GIGABIT_FULL | Abilities::PAUSE | Abilities::AUTONEG)
+        );
+
+        // Without gigabit on our side the link falls back to 100 full
+        phy.registers[MII_CTRL1000 as usize] = 0;
+        assert_eq!(link_mode(&mut phy), Ok(Some(LinkMode { speed_mbps: 100, full_duplex: true })));
+        phy.registers[MII_BMSR as usize] &= !BMSR_LSTATUS;
+        assert_eq!(link_mode(&mut phy), Ok(None));
+    }
+
+    #[test]
+    fn test_restarts_and_forces() {
+        let mut phy = FakePhy::new();
+        let advertise = Abilities(Abilities::HUNDRED_FULL | Abilities::GIGABIT_HALF | Abilities::PAUSE);
+        restart_autoneg(&mut phy, advertise).unwrap();
+        // 1000 half is not something this PHY does
+        assert_eq!(phy.registers[MII_ADVERTISE as usize], ADVERTISE_CSMA | ADVERTISE_100FULL | ADVERTISE_PAUSE);
+        assert_eq!(phy.registers[MII_CTRL1000 as usize], 0);
+        assert_ne!(phy.registers[MII_BMCR as usize] & BMCR_ANRESTART, 0);
+        assert_eq!(restart_autoneg(&mut phy, Abilities(Abilities::GIGABIT_HALF)), Err(PhyError::InvalidArgument));
+
+        assert_eq!(
+            force_mode(&mut phy, LinkMode { speed_mbps: 1000, full_duplex: true }),
+            Err(PhyError::InvalidArgument)
+        );
+        force_mode(&mut phy, LinkMode { speed_mbps: 100, full_duplex: false }).unwrap();
+        assert_eq!(phy.registers[MII_BMCR as usize], BMCR_SPEED100);
+        assert_eq!(link_mode(&mut phy), Ok(Some(LinkMode { speed_mbps: 100, full_duplex: false })));
+    }
+}
@@ -0,0 +1,31 @@
+/*
+ * Orion Operating System - ICMP Echo
+ *
+ * Client side of the network server's echo sockets (see
+ * services/net/ping.h) and the bookkeeping a ping tool needs around
+ * them: which requests are still outstanding, round-trip times,
+ * duplicates and packet loss. UDP probe sockets, hop bookkeeping and the
+ * path MTU search serve traceroute.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod pmtu;
+pub mod socket;
+pub mod stats;
+pub mod trace;
+pub mod udp;
+
+pub use pmtu::PmtuSearch;
+pub use socket::{EchoKind, EchoMessage, EchoSocket, Family};
+pub use stats::{PingStats, ReplyOutcome};
+pub use trace::{Hop, ProbeAnswer, ProbeResult, Tracer};
+pub use udp::{ProbeError, ProbeSocket};
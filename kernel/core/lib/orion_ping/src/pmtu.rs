@@ -0,0 +1,130 @@
+/*
+ * Orion Operating System - Path MTU Search
+ *
+ * Finds the largest packet that crosses a path with don't-fragment set.
+ * Sizes are whole IPv4 packets. The search starts by trying the upper
+ * bound, then follows the next-hop MTU that a fragmentation needed error
+ * reports; routers that drop oversized packets silently (or without
+ * a hint) are handled by bisecting between the largest size that got
+ * through and the smallest that did not.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+/// Smallest MTU every IPv4 link must carry (RFC 791)
+pub const MIN_MTU: u16 = 68;
+
+#[derive(Debug, Clone)]
+pub struct PmtuSearch {
+    /// Largest size known to get through
+    low: u16,
+    /// Largest size that may still get through
+    high: u16,
+    /// Size to try next instead of the midpoint
+    hint: Option<u16>,
+}
+
+impl PmtuSearch {
+    pub fn new(max: u16) -> Self {
+        let max = max.max(MIN_MTU);
+        Self {
+            low: MIN_MTU,
+            high: max,
+            hint: Some(max),
+        }
+    }
+
+    /// Packet size to probe next, None once the path MTU is known
+    pub fn next_size(&self) -> Option<u16> {
+        if self.low >= self.high {
+            return None;
+        }
+        if let Some(hint) = self.hint {
+            return Some(hint);
+        }
+        // Round up so the search always moves past `low`
+        Some(self.low + (self.high - self.low).div_ceil(2))
+    }
+
+    /// A probe of `size` got through
+    pub fn passed(&mut self, size: u16) {
+        self.hint = None;
+        if size > self.low {
+            self.low = size.min(self.high);
+        }
+    }
+
+    /// A probe of `size` was refused; `mtu` is the next-hop MTU the error
+    /// carried, 0 when there was none
+    pub fn too_big(&mut self, size: u16, mtu: u16) {
+        self.hint = None;
+        if size <= self.low {
+            // Contradicts an earlier success; the path changed
+            self.low = MIN_MTU;
+        }
+        self.high = self.high.min(size.saturating_sub(1)).max(self.low);
+        if mtu > self.low && mtu < size {
+            self.high = self.high.min(mtu);
+            self.hint = Some(mtu);
+        }
+    }
+
+    /// Nothing came back for a probe of `size`; treated as refused
+    pub fn lost(&mut self, size: u16) {
+        self.too_big(size, 0);
+    }
+
+    /// Largest size known to get through so far
+    pub fn mtu(&self) -> u16 {
+        self.low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a search against a path that passes packets up to `path` and
+    /// reports `hint` (0 for none) for larger ones, returning the probes
+    fn run(max: u16, path: u16, hint: u16) -> (u16, usize) {
+        let mut search = PmtuSearch::new(max);
+        let mut probes = 0;
+        while let Some(size) = search.next_size() {
+            probes += 1;
+            if size <= path {
+                search.passed(size);
+            } else {
+                search.too_big(size, hint);
+            }
+            assert!(probes < 32);
+        }
+        (search.mtu(), probes)
+    }
+
+    #[test]
+    fn test_follows_the_reported_mtu() {
+        assert_eq!(run(1500, 1500, 0), (1500, 1));
+        assert_eq!(run(1500, 1400, 1400), (1400, 2));
+    }
+
+    #[test]
+    fn test_bisects_black_holes() {
+        let (mtu, probes) = run(1500, 1280, 0);
+        assert_eq!(mtu, 1280);
+        assert!(probes <= 12);
+        assert_eq!(run(1500, MIN_MTU, 0).0, MIN_MTU);
+    }
+
+    #[test]
+    fn test_ignores_useless_hints() {
+        // A hint at or above the refused size says nothing new
+        assert_eq!(run(1500, 1000, 1500).0, 1000);
+        let mut search = PmtuSearch::new(9000);
+        search.lost(9000);
+        assert!(search.next_size().unwrap() < 9000);
+    }
+}
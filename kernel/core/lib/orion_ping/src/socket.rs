@@ -0,0 +1,275 @@
+/*
+ * Orion Operating System - Echo Sockets
+ *
+ * One echo socket per ping session. The network server fills its own
+ * identifier into the requests and only queues replies and ICMP errors
+ * carrying it, so sessions never see each other's traffic. RECV answers
+ * -EAGAIN rather than blocking; callers poll.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_http::socket::NetChannel;
+
+// Opcodes
+pub const OP_OPEN: u32 = 32;
+pub const OP_SEND: u32 = 33;
+pub const OP_RECV: u32 = 34;
+pub const OP_CLOSE: u32 = 35;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EMSGSIZE: i32 = -90;
+pub const STATUS_EAFNOSUPPORT: i32 = -97;
+pub const STATUS_EHOSTUNREACH: i32 = -113;
+
+/// Largest echo data the network server sends or returns
+pub const MAX_PAYLOAD: usize = 1472;
+
+// SEND flags
+const FLAG_DONT_FRAGMENT: u8 = 1 << 0;
+
+// ICMP types the server queues
+const TYPE_ECHO_REPLY: u8 = 0;
+const TYPE_DEST_UNREACHABLE: u8 = 3;
+const TYPE_TIME_EXCEEDED: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    Inet,
+    Inet6,
+}
+
+impl Family {
+    fn wire(self) -> u8 {
+        match self {
+            Family::Inet => 2,
+            Family::Inet6 => 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoKind {
+    Reply,
+    /// Destination unreachable, with the ICMP code
+    Unreachable(u8),
+    /// TTL ran out on the way, with the ICMP code
+    TimeExceeded(u8),
+    Other {
+        kind: u8,
+        code: u8,
+    },
+}
+
+impl EchoKind {
+    pub(crate) fn from_wire(kind: u8, code: u8) -> Self {
+        match kind {
+            TYPE_ECHO_REPLY => EchoKind::Reply,
+            TYPE_DEST_UNREACHABLE => EchoKind::Unreachable(code),
+            TYPE_TIME_EXCEEDED => EchoKind::TimeExceeded(code),
+            _ => EchoKind::Other { kind, code },
+        }
+    }
+}
+
+/// Reply or error read from an echo socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EchoMessage {
+    /// Host that answered, or the router reporting an error
+    pub source: u32,
+    pub kind: EchoKind,
+    pub sequence: u16,
+    pub ttl: u8,
+    /// Next-hop MTU reported with a fragmentation needed error, else 0
+    pub mtu: u16,
+    /// Echo data; for errors, what the quoted request carried
+    pub data: Vec<u8>,
+}
+
+impl EchoMessage {
+    fn decode(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 12 {
+            return None;
+        }
+        Some(Self {
+            source: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            kind: EchoKind::from_wire(payload[4], payload[5]),
+            sequence: u16::from_le_bytes([payload[6], payload[7]]),
+            ttl: payload[8],
+            mtu: u16::from_le_bytes([payload[10], payload[11]]),
+            data: payload[12..].to_vec(),
+        })
+    }
+}
+
+/// Issue one request and split the reply into status and payload
+pub(crate) fn request<C: NetChannel>(channel: &mut C, op: u32, args: &[u8]) -> Result<Vec<u8>, i32> {
+    let mut message = Vec::with_capacity(4 + args.len());
+    message.extend_from_slice(&op.to_le_bytes());
+    message.extend_from_slice(args);
+
+    let reply = channel.call(&message).ok_or(STATUS_EIO)?;
+    if reply.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]]) {
+        STATUS_OK => Ok(reply[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+pub struct EchoSocket<C: NetChannel> {
+    channel: C,
+    socket: u32,
+    identifier: u16,
+    dont_fragment: bool,
+}
+
+impl<C: NetChannel> EchoSocket<C> {
+    /// Open an echo socket; needs the raw network capability (-EPERM
+    /// otherwise) and answers -EAFNOSUPPORT while IPv6 is not served
+    pub fn open(mut channel: C, family: Family) -> Result<Self, i32> {
+        let payload = request(&mut channel, OP_OPEN, &[family.wire()])?;
+        if payload.len() < 6 {
+            return Err(STATUS_EIO);
+        }
+        Ok(Self {
+            socket: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            identifier: u16::from_le_bytes([payload[4], payload[5]]),
+            dont_fragment: false,
+            channel,
+        })
+    }
+
+    /// ICMP identifier the server put in this socket's requests
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    /// Set the don't-fragment bit on later requests; a request larger than
+    /// the route MTU then fails with -EMSGSIZE instead of being fragmented
+    pub fn set_dont_fragment(&mut self, dont_fragment: bool) {
+        self.dont_fragment = dont_fragment;
+    }
+
+    /// Send an echo request to `destination`; a `ttl` of 0 is the server default
+    pub fn send(&mut self, destination: u32, sequence: u16, ttl: u8, data: &[u8]) -> Result<(), i32> {
+        let mut args = Vec::with_capacity(12 + data.len());
+        args.extend_from_slice(&self.socket.to_le_bytes());
+        args.extend_from_slice(&destination.to_le_bytes());
+        args.extend_from_slice(&sequence.to_le_bytes());
+        args.push(ttl);
+        args.push(if self.dont_fragment { FLAG_DONT_FRAGMENT } else { 0 });
+        args.extend_from_slice(data);
+        request(&mut self.channel, OP_SEND, &args).map(|_| ())
+    }
+
+    /// Next queued reply or error, None while there is none
+    pub fn recv(&mut self) -> Result<Option<EchoMessage>, i32> {
+        let mut args = Vec::with_capacity(8);
+        args.extend_from_slice(&self.socket.to_le_bytes());
+        args.extend_from_slice(&(MAX_PAYLOAD as u32).to_le_bytes());
+        match request(&mut self.channel, OP_RECV, &args) {
+            Ok(payload) => EchoMessage::decode(&payload).map(Some).ok_or(STATUS_EIO),
+            Err(STATUS_EAGAIN) => Ok(None),
+            Err(status) => Err(status),
+        }
+    }
+}
+
+impl<C: NetChannel> Drop for EchoSocket<C> {
+    fn drop(&mut self) {
+        let _ = request(&mut self.channel, OP_CLOSE, &self.socket.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+    use orion_proto::reply;
+
+    /// Replays canned replies and records the requests
+    struct Script {
+        replies: VecDeque<Vec<u8>>,
+        requests: Vec<Vec<u8>>,
+    }
+
+    impl NetChannel for &mut Script {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            self.requests.push(request.to_vec());
+            self.replies.pop_front()
+        }
+    }
+
+    #[test]
+    fn test_exchanges_echo_messages() {
+        let mut message = vec![0u8; 12];
+        message[..4].copy_from_slice(&0x0A00_0001u32.to_le_bytes());
+        message[4] = TYPE_TIME_EXCEEDED;
+        message[6..8].copy_from_slice(&7u16.to_le_bytes());
+        message[8] = 250;
+        message[10..12].copy_from_slice(&1400u16.to_le_bytes());
+        message.extend_from_slice(b"abc");
+
+        let mut script = Script {
+            replies: VecDeque::from(vec![
+                reply(STATUS_OK, &[3, 0, 0, 0, 0x34, 0x12]),
+                reply(STATUS_OK, &[]),
+                reply(STATUS_EAGAIN, &[]),
+                reply(STATUS_OK, &message),
+                reply(STATUS_OK, &[]),
+            ]),
+            requests: Vec::new(),
+        };
+        {
+            let mut socket = EchoSocket::open(&mut script, Family::Inet).unwrap();
+            assert_eq!(socket.identifier(), 0x1234);
+            socket.set_dont_fragment(true);
+            socket.send(0x0808_0808, 7, 0, b"abc").unwrap();
+            assert_eq!(socket.recv(), Ok(None));
+
+            let received = socket.recv().unwrap().unwrap();
+            assert_eq!(received.source, 0x0A00_0001);
+            assert_eq!(received.kind, EchoKind::TimeExceeded(0));
+            assert_eq!(received.sequence, 7);
+            assert_eq!(received.ttl, 250);
+            assert_eq!(received.mtu, 1400);
+            assert_eq!(received.data, b"abc");
+        }
+
+        assert_eq!(script.requests[0], [&OP_OPEN.to_le_bytes()[..], &[2]].concat());
+        let send = &script.requests[1];
+        assert_eq!(&send[..4], &OP_SEND.to_le_bytes());
+        assert_eq!(&send[4..8], &3u32.to_le_bytes());
+        assert_eq!(&send[8..12], &0x0808_0808u32.to_le_bytes());
+        assert_eq!(send[15], FLAG_DONT_FRAGMENT);
+        assert_eq!(&send[16..], b"abc");
+        // Dropping the socket closes it
+        assert_eq!(&script.requests[4][..4], &OP_CLOSE.to_le_bytes());
+    }
+
+    #[test]
+    fn test_reports_unsupported_family() {
+        let mut script = Script {
+            replies: VecDeque::from(vec![reply(STATUS_EAFNOSUPPORT, &[])]),
+            requests: Vec::new(),
+        };
+        assert!(matches!(
+            EchoSocket::open(&mut script, Family::Inet6),
+            Err(STATUS_EAFNOSUPPORT)
+        ));
+        assert_eq!(script.requests[0][4], 10);
+    }
+}
@@ -0,0 +1,215 @@
+/*
+ * Orion Operating System - Ping Statistics
+ *
+ * Tracks the echo requests of one session by sequence number. Round-trip
+ * times are measured against the send time kept here rather than a
+ * timestamp in the echo data, so any payload size works. A request
+ * counts as lost once it expired or an ICMP error answered it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+/// What a reply turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyOutcome {
+    /// First reply to an outstanding request
+    Answered { rtt_ns: u64 },
+    /// Another reply to a request already answered
+    Duplicate,
+    /// Reply to a request that expired or was never sent
+    Unexpected,
+}
+
+#[derive(Debug, Default)]
+pub struct PingStats {
+    transmitted: u32,
+    received: u32,
+    duplicates: u32,
+    errors: u32,
+    /// Send time of each outstanding request
+    pending: BTreeMap<u16, u64>,
+    answered: BTreeSet<u16>,
+    rtt_min: u64,
+    rtt_max: u64,
+    rtt_sum: u64,
+    rtt_sum_squares: u128,
+}
+
+impl PingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A request left at `now`
+    pub fn sent(&mut self, sequence: u16, now: u64) {
+        self.transmitted += 1;
+        self.answered.remove(&sequence);
+        self.pending.insert(sequence, now);
+    }
+
+    /// An echo reply arrived at `now`
+    pub fn reply(&mut self, sequence: u16, now: u64) -> ReplyOutcome {
+        let Some(sent_at) = self.pending.remove(&sequence) else {
+            if self.answered.contains(&sequence) {
+                self.duplicates += 1;
+                return ReplyOutcome::Duplicate;
+            }
+            return ReplyOutcome::Unexpected;
+        };
+        self.answered.insert(sequence);
+
+        let rtt = now.saturating_sub(sent_at);
+        self.rtt_min = if self.received == 0 { rtt } else { self.rtt_min.min(rtt) };
+        self.rtt_max = self.rtt_max.max(rtt);
+        self.rtt_sum += rtt;
+        self.rtt_sum_squares += (rtt as u128) * (rtt as u128);
+        self.received += 1;
+        ReplyOutcome::Answered { rtt_ns: rtt }
+    }
+
+    /// An ICMP error answered a request; returns whether it was outstanding
+    pub fn error(&mut self, sequence: u16) -> bool {
+        let outstanding = self.pending.remove(&sequence).is_some();
+        if outstanding {
+            self.errors += 1;
+        }
+        outstanding
+    }
+
+    /// Give up on requests older than `timeout_ns`, returning how many
+    pub fn expire(&mut self, now: u64, timeout_ns: u64) -> usize {
+        let before = self.pending.len();
+        self.pending
+            .retain(|_, sent_at| now.saturating_sub(*sent_at) < timeout_ns);
+        before - self.pending.len()
+    }
+
+    /// Requests neither answered nor expired
+    pub fn outstanding(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn transmitted(&self) -> u32 {
+        self.transmitted
+    }
+
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    pub fn duplicates(&self) -> u32 {
+        self.duplicates
+    }
+
+    pub fn errors(&self) -> u32 {
+        self.errors
+    }
+
+    /// Share of requests without a reply, in percent rounded down
+    pub fn loss_percent(&self) -> u32 {
+        if self.transmitted == 0 {
+            return 0;
+        }
+        ((self.transmitted - self.received) as u64 * 100 / self.transmitted as u64) as u32
+    }
+
+    pub fn rtt_min(&self) -> Option<u64> {
+        (self.received > 0).then_some(self.rtt_min)
+    }
+
+    pub fn rtt_max(&self) -> Option<u64> {
+        (self.received > 0).then_some(self.rtt_max)
+    }
+
+    pub fn rtt_avg(&self) -> Option<u64> {
+        (self.received > 0).then(|| self.rtt_sum / self.received as u64)
+    }
+
+    /// Standard deviation of the round-trip times
+    pub fn rtt_mdev(&self) -> Option<u64> {
+        if self.received == 0 {
+            return None;
+        }
+        let count = self.received as u128;
+        let mean = self.rtt_sum as u128 / count;
+        let variance = (self.rtt_sum_squares / count).saturating_sub(mean * mean);
+        Some(isqrt(variance) as u64)
+    }
+}
+
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from an estimate that is never too small
+    let mut estimate = 1u128 << ((128 - value.leading_zeros()).div_ceil(2));
+    loop {
+        let next = (estimate + value / estimate) / 2;
+        if next >= estimate {
+            return estimate;
+        }
+        estimate = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_measures_round_trips() {
+        let mut stats = PingStats::new();
+        stats.sent(1, 0);
+        stats.sent(2, 1000 * MS);
+        stats.sent(3, 2000 * MS);
+        assert_eq!(stats.reply(1, 10 * MS), ReplyOutcome::Answered { rtt_ns: 10 * MS });
+        assert_eq!(stats.reply(2, 1030 * MS), ReplyOutcome::Answered { rtt_ns: 30 * MS });
+        assert_eq!(stats.reply(2, 1031 * MS), ReplyOutcome::Duplicate);
+        assert_eq!(stats.reply(9, 1032 * MS), ReplyOutcome::Unexpected);
+
+        assert_eq!(stats.rtt_min(), Some(10 * MS));
+        assert_eq!(stats.rtt_max(), Some(30 * MS));
+        assert_eq!(stats.rtt_avg(), Some(20 * MS));
+        assert_eq!(stats.rtt_mdev(), Some(10 * MS));
+        assert_eq!(stats.duplicates(), 1);
+        assert_eq!(stats.outstanding(), 1);
+    }
+
+    #[test]
+    fn test_counts_losses() {
+        let mut stats = PingStats::new();
+        assert_eq!(stats.loss_percent(), 0);
+        assert_eq!(stats.rtt_avg(), None);
+
+        for sequence in 0..3 {
+            stats.sent(sequence, sequence as u64 * 1000 * MS);
+        }
+        assert!(stats.error(0));
+        assert!(!stats.error(0));
+        assert_eq!(stats.expire(2500 * MS, 1000 * MS), 1);
+        assert_eq!(stats.reply(1, 2600 * MS), ReplyOutcome::Unexpected);
+        assert!(matches!(stats.reply(2, 2700 * MS), ReplyOutcome::Answered { .. }));
+
+        assert_eq!(stats.transmitted(), 3);
+        assert_eq!(stats.received(), 1);
+        assert_eq!(stats.errors(), 1);
+        assert_eq!(stats.loss_percent(), 66);
+        assert_eq!(stats.outstanding(), 0);
+    }
+
+    #[test]
+    fn test_integer_square_root() {
+        for value in [0u128, 1, 2, 3, 4, 15, 16, 17, 1 << 40, u64::MAX as u128] {
+            let root = isqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+/*
+ * Orion Operating System - Route Tracing
+ *
+ * Hop-by-hop bookkeeping for traceroute. Each hop sends a few probes
+ * with the same TTL; a probe is told apart by a key the tool puts in the
+ * packet (the echo sequence number, or the offset of the UDP destination
+ * port) so answers can arrive in any order. The trace ends with the hop
+ * the destination itself answered at, or after the last hop allowed.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::socket::EchoKind;
+
+/// How the host answering a probe treated it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeAnswer {
+    /// TTL ran out at a router on the way
+    Transit,
+    /// The destination answered: echo reply, or port unreachable for UDP
+    Reached,
+    /// Another destination unreachable, with the ICMP code
+    Unreachable(u8),
+}
+
+impl ProbeAnswer {
+    /// Classify an answer; None for ICMP messages traceroute ignores
+    pub fn from_kind(kind: EchoKind) -> Option<Self> {
+        match kind {
+            EchoKind::TimeExceeded(_) => Some(ProbeAnswer::Transit),
+            EchoKind::Reply | EchoKind::Unreachable(3) => Some(ProbeAnswer::Reached),
+            EchoKind::Unreachable(code) => Some(ProbeAnswer::Unreachable(code)),
+            EchoKind::Other { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeResult {
+    Answered {
+        from: u32,
+        rtt_ns: u64,
+        answer: ProbeAnswer,
+    },
+    TimedOut,
+}
+
+/// Probes of one TTL in the order they were sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hop {
+    pub ttl: u8,
+    pub probes: Vec<ProbeResult>,
+}
+
+struct Pending {
+    key: u16,
+    sent_at: u64,
+    result: Option<ProbeResult>,
+}
+
+pub struct Tracer {
+    max_hops: u8,
+    probes_per_hop: usize,
+    ttl: u8,
+    next_key: u16,
+    probes: Vec<Pending>,
+    finished: bool,
+}
+
+impl Tracer {
+    pub fn new(first_ttl: u8, max_hops: u8, probes_per_hop: usize) -> Self {
+        Self {
+            max_hops,
+            probes_per_hop: probes_per_hop.max(1),
+            ttl: first_ttl.max(1),
+            next_key: 0,
+            probes: Vec::new(),
+            finished: first_ttl > max_hops,
+        }
+    }
+
+    /// TTL of the hop being probed
+    pub fn ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Key for the next probe of the current hop, None once all are out
+    pub fn next_probe(&mut self, now: u64) -> Option<u16> {
+        if self.finished || self.probes.len() >= self.probes_per_hop {
+            return None;
+        }
+        let key = self.next_key;
+        self.next_key = self.next_key.wrapping_add(1);
+        self.probes.push(Pending {
+            key,
+            sent_at: now,
+            result: None,
+        });
+        Some(key)
+    }
+
+    /// Record an answer; false if it is not for a probe of this hop still
+    /// waiting, as late answers to earlier hops are not
+    pub fn answer(&mut self, key: u16, from: u32, answer: ProbeAnswer, now: u64) -> bool {
+        let Some(probe) = self
+            .probes
+            .iter_mut()
+            .find(|probe| probe.key == key && probe.result.is_none())
+        else {
+            return false;
+        };
+        probe.result = Some(ProbeResult::Answered {
+            from,
+            rtt_ns: now.saturating_sub(probe.sent_at),
+            answer,
+        });
+        true
+    }
+
+    /// Give up on probes older than `timeout_ns`
+    pub fn expire(&mut self, now: u64, timeout_ns: u64) {
+        for probe in self.probes.iter_mut() {
+            if probe.result.is_none() && now.saturating_sub(probe.sent_at) >= timeout_ns {
+                probe.result = Some(ProbeResult::TimedOut);
+            }
+        }
+    }
+
+    /// Every probe of the hop went out and was answered or timed out
+    pub fn hop_complete(&self) -> bool {
+        self.probes.len() >= self.probes_per_hop && self.probes.iter().all(|probe| probe.result.is_some())
+    }
+
+    /// Close the current hop and move to the next TTL. The trace is over
+    /// once any probe reached the destination or drew an unreachable,
+    /// or after `max_hops`.
+    pub fn finish_hop(&mut self) -> Hop {
+        let probes: Vec<ProbeResult> = self
+            .probes
+            .drain(..)
+            .map(|probe| probe.result.unwrap_or(ProbeResult::TimedOut))
+            .collect();
+        let ended = probes.iter().any(|result| {
+            matches!(
+                result,
+                ProbeResult::Answered { answer, .. } if *answer != ProbeAnswer::Transit
+            )
+        });
+        let hop = Hop { ttl: self.ttl, probes };
+        if ended || self.ttl >= self.max_hops {
+            self.finished = true;
+        } else {
+            self.ttl += 1;
+        }
+        hop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_walks_hops_until_the_destination() {
+        let mut tracer = Tracer::new(1, 30, 2);
+        assert_eq!(tracer.next_probe(0), Some(0));
+        assert_eq!(tracer.next_probe(MS), Some(1));
+        assert_eq!(tracer.next_probe(MS), None);
+        assert!(tracer.answer(1, 0x0A00_0001, ProbeAnswer::Transit, 5 * MS));
+        assert!(!tracer.answer(1, 0x0A00_0001, ProbeAnswer::Transit, 6 * MS));
+        assert!(!tracer.hop_complete());
+        tracer.expire(1000 * MS, 1000 * MS);
+        assert!(tracer.hop_complete());
+
+        let hop = tracer.finish_hop();
+        assert_eq!(hop.ttl, 1);
+        assert_eq!(hop.probes[0], ProbeResult::TimedOut);
+        assert_eq!(
+            hop.probes[1],
+            ProbeResult::Answered {
+                from: 0x0A00_0001,
+                rtt_ns: 4 * MS,
+                answer: ProbeAnswer::Transit
+            }
+        );
+        assert!(!tracer.finished());
+        assert_eq!(tracer.ttl(), 2);
+
+        // Keys keep counting, so a late answer to hop 1 is not taken for hop 2
+        assert_eq!(tracer.next_probe(2000 * MS), Some(2));
+        assert_eq!(tracer.next_probe(2000 * MS), Some(3));
+        assert!(!tracer.answer(0, 0x0A00_0001, ProbeAnswer::Transit, 2001 * MS));
+        assert!(tracer.answer(2, 0x0808_0808, ProbeAnswer::Reached, 2010 * MS));
+        assert!(tracer.answer(3, 0x0808_0808, ProbeAnswer::Reached, 2011 * MS));
+        assert!(tracer.hop_complete());
+        tracer.finish_hop();
+        assert!(tracer.finished());
+        assert_eq!(tracer.next_probe(2020 * MS), None);
+    }
+
+    #[test]
+    fn test_stops_after_max_hops() {
+        let mut tracer = Tracer::new(1, 2, 1);
+        for _ in 0..2 {
+            tracer.next_probe(0);
+            tracer.expire(10, 1);
+            assert_eq!(tracer.finish_hop().probes, [ProbeResult::TimedOut]);
+        }
+        assert!(tracer.finished());
+    }
+
+    #[test]
+    fn test_classifies_answers() {
+        assert_eq!(
+            ProbeAnswer::from_kind(EchoKind::TimeExceeded(0)),
+            Some(ProbeAnswer::Transit)
+        );
+        assert_eq!(
+            ProbeAnswer::from_kind(EchoKind::Unreachable(3)),
+            Some(ProbeAnswer::Reached)
+        );
+        assert_eq!(ProbeAnswer::from_kind(EchoKind::Reply), Some(ProbeAnswer::Reached));
+        assert_eq!(
+            ProbeAnswer::from_kind(EchoKind::Unreachable(1)),
+            Some(ProbeAnswer::Unreachable(1))
+        );
+        assert_eq!(ProbeAnswer::from_kind(EchoKind::Other { kind: 5, code: 0 }), None);
+    }
+}
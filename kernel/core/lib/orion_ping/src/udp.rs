@@ -0,0 +1,202 @@
+/*
+ * Orion Operating System - UDP Probe Sockets
+ *
+ * An ordinary UDP socket of the network server (see
+ * services/net/socket_ipc.h) with the two options probing needs, unicast
+ * TTL and don't-fragment, and the ICMP errors its datagrams drew read
+ * back through RECVERR. Unlike echo sockets no capability is required.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_http::socket::NetChannel;
+
+use crate::socket::{request, EchoKind, STATUS_EAGAIN, STATUS_EIO};
+
+// Opcodes
+pub const OP_CLOSE: u32 = 6;
+pub const OP_UDP_BIND: u32 = 7;
+pub const OP_SENDTO: u32 = 8;
+pub const OP_SETSOCKOPT: u32 = 11;
+pub const OP_RECVERR: u32 = 14;
+
+// SETSOCKOPT options
+const OPT_TTL: u32 = 6;
+const OPT_DONT_FRAGMENT: u32 = 7;
+
+/// ICMP error drawn by a datagram of a probe socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeError {
+    /// Router or host that reported the error
+    pub reporter: u32,
+    pub kind: EchoKind,
+    /// Next-hop MTU of a fragmentation needed error, else 0
+    pub mtu: u16,
+    /// Where the quoted datagram was going
+    pub destination: u32,
+    pub port: u16,
+}
+
+impl ProbeError {
+    fn decode(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 14 {
+            return None;
+        }
+        Some(Self {
+            reporter: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            kind: EchoKind::from_wire(payload[4], payload[5]),
+            mtu: u16::from_le_bytes([payload[6], payload[7]]),
+            destination: u32::from_le_bytes([payload[8], payload[9], payload[10], payload[11]]),
+            port: u16::from_le_bytes([payload[12], payload[13]]),
+        })
+    }
+}
+
+pub struct ProbeSocket<C: NetChannel> {
+    channel: C,
+    socket: u32,
+    port: u16,
+}
+
+impl<C: NetChannel> ProbeSocket<C> {
+    /// Bind a UDP socket to `port` on every interface, 0 for an ephemeral one
+    pub fn bind(mut channel: C, port: u16) -> Result<Self, i32> {
+        let mut args = Vec::with_capacity(6);
+        args.extend_from_slice(&0u32.to_le_bytes());
+        args.extend_from_slice(&port.to_le_bytes());
+        let payload = request(&mut channel, OP_UDP_BIND, &args)?;
+        if payload.len() < 6 {
+            return Err(STATUS_EIO);
+        }
+        Ok(Self {
+            socket: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            port: u16::from_le_bytes([payload[4], payload[5]]),
+            channel,
+        })
+    }
+
+    /// Local port the datagrams leave from
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn set_option(&mut self, option: u32, value: u32) -> Result<(), i32> {
+        let mut args = Vec::with_capacity(12);
+        args.extend_from_slice(&self.socket.to_le_bytes());
+        args.extend_from_slice(&option.to_le_bytes());
+        args.extend_from_slice(&value.to_le_bytes());
+        request(&mut self.channel, OP_SETSOCKOPT, &args).map(|_| ())
+    }
+
+    /// TTL of later datagrams, 1 to 255
+    pub fn set_ttl(&mut self, ttl: u8) -> Result<(), i32> {
+        self.set_option(OPT_TTL, ttl as u32)
+    }
+
+    /// With the bit set, datagrams larger than the route MTU fail with
+    /// -EMSGSIZE and routers answer fragmentation needed
+    pub fn set_dont_fragment(&mut self, dont_fragment: bool) -> Result<(), i32> {
+        self.set_option(OPT_DONT_FRAGMENT, dont_fragment as u32)
+    }
+
+    pub fn send_to(&mut self, destination: u32, port: u16, data: &[u8]) -> Result<(), i32> {
+        let mut args = Vec::with_capacity(10 + data.len());
+        args.extend_from_slice(&self.socket.to_le_bytes());
+        args.extend_from_slice(&destination.to_le_bytes());
+        args.extend_from_slice(&port.to_le_bytes());
+        args.extend_from_slice(data);
+        request(&mut self.channel, OP_SENDTO, &args).map(|_| ())
+    }
+
+    /// Next queued ICMP error, None while there is none
+    pub fn recv_error(&mut self) -> Result<Option<ProbeError>, i32> {
+        match request(&mut self.channel, OP_RECVERR, &self.socket.to_le_bytes()) {
+            Ok(payload) => ProbeError::decode(&payload).map(Some).ok_or(STATUS_EIO),
+            Err(STATUS_EAGAIN) => Ok(None),
+            Err(status) => Err(status),
+        }
+    }
+}
+
+impl<C: NetChannel> Drop for ProbeSocket<C> {
+    fn drop(&mut self) {
+        let _ = request(&mut self.channel, OP_CLOSE, &self.socket.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket::{STATUS_EMSGSIZE, STATUS_OK};
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+    use orion_proto::reply;
+
+    struct Script {
+        replies: VecDeque<Vec<u8>>,
+        requests: Vec<Vec<u8>>,
+    }
+
+    impl NetChannel for &mut Script {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            self.requests.push(request.to_vec());
+            self.replies.pop_front()
+        }
+    }
+
+    #[test]
+    fn test_probes_and_reads_errors() {
+        let mut error = vec![0u8; 14];
+        error[..4].copy_from_slice(&0x0A00_00FEu32.to_le_bytes());
+        error[4] = 3;
+        error[5] = 4;
+        error[6..8].copy_from_slice(&1280u16.to_le_bytes());
+        error[8..12].copy_from_slice(&0x0808_0808u32.to_le_bytes());
+        error[12..14].copy_from_slice(&33434u16.to_le_bytes());
+
+        let mut script = Script {
+            replies: VecDeque::from(vec![
+                reply(STATUS_OK, &[5, 0, 0, 0, 0x10, 0xC0]),
+                reply(STATUS_OK, &[]),
+                reply(STATUS_OK, &[]),
+                reply(STATUS_EMSGSIZE, &[]),
+                reply(STATUS_EAGAIN, &[]),
+                reply(STATUS_OK, &error),
+                reply(STATUS_OK, &[]),
+            ]),
+            requests: Vec::new(),
+        };
+        {
+            let mut socket = ProbeSocket::bind(&mut script, 0).unwrap();
+            assert_eq!(socket.port(), 0xC010);
+            socket.set_ttl(3).unwrap();
+            socket.set_dont_fragment(true).unwrap();
+            assert_eq!(socket.send_to(0x0808_0808, 33434, &[0; 1600]), Err(STATUS_EMSGSIZE));
+            assert_eq!(socket.recv_error(), Ok(None));
+            assert_eq!(
+                socket.recv_error(),
+                Ok(Some(ProbeError {
+                    reporter: 0x0A00_00FE,
+                    kind: EchoKind::Unreachable(4),
+                    mtu: 1280,
+                    destination: 0x0808_0808,
+                    port: 33434,
+                }))
+            );
+        }
+
+        let ttl = &script.requests[1];
+        assert_eq!(&ttl[..4], &OP_SETSOCKOPT.to_le_bytes());
+        assert_eq!(&ttl[8..], &[OPT_TTL.to_le_bytes(), 3u32.to_le_bytes()].concat()[..]);
+        let send = &script.requests[3];
+        assert_eq!(&send[12..14], &33434u16.to_le_bytes());
+        assert_eq!(send.len(), 14 + 1600);
+        assert_eq!(&script.requests[6][..4], &OP_CLOSE.to_le_bytes());
+    }
+}
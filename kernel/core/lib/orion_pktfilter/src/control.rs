@@ -0,0 +1,133 @@
+/*
+ * Orion Operating System - Packet Filter Control
+ *
+ * Management requests NIC drivers accept through an ioctl, shared here so
+ * every driver installs, removes and reports filters the same way.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::filter::{PacketFilter, RuleCounters};
+use crate::program::FilterProgram;
+
+/// Ioctl carrying a filter control request; the reply is sent back as data
+pub const FILTER_IOCTL_CONTROL: u32 = 0x2010;
+
+// Control opcodes
+pub const CTRL_SET_PROGRAM: u32 = 1;
+pub const CTRL_CLEAR_PROGRAM: u32 = 2;
+pub const CTRL_READ_COUNTERS: u32 = 3;
+
+// Control reply status codes
+pub const CTRL_OK: i32 = 0;
+pub const CTRL_ENOENT: i32 = -2;
+pub const CTRL_EINVAL: i32 = -22;
+
+fn put_counters(out: &mut Vec<u8>, counters: &RuleCounters) {
+    out.extend_from_slice(&counters.packets.to_le_bytes());
+    out.extend_from_slice(&counters.bytes.to_le_bytes());
+    out.extend_from_slice(&counters.dropped.to_le_bytes());
+}
+
+fn control(filter: &mut Option<PacketFilter>, request: &[u8], queues: u8, out: &mut Vec<u8>) -> i32 {
+    let opcode = match request.get(0..4) {
+        Some(bytes) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        None => return CTRL_EINVAL,
+    };
+    match opcode {
+        CTRL_SET_PROGRAM => match FilterProgram::decode(&request[4..], queues) {
+            Ok(program) => {
+                *filter = Some(PacketFilter::new(program));
+                CTRL_OK
+            }
+            Err(_) => CTRL_EINVAL,
+        },
+        CTRL_CLEAR_PROGRAM => match filter.take() {
+            Some(_) => CTRL_OK,
+            None => CTRL_ENOENT,
+        },
+        CTRL_READ_COUNTERS => match filter {
+            Some(filter) => {
+                out.extend_from_slice(&(filter.counters().len() as u32).to_le_bytes());
+                for counters in filter.counters() {
+                    put_counters(out, counters);
+                }
+                put_counters(out, &filter.default_counters());
+                CTRL_OK
+            }
+            None => CTRL_ENOENT,
+        },
+        _ => CTRL_EINVAL,
+    }
+}
+
+/// Serve a filter request (u32 opcode then arguments) for a device with
+/// `queues` receive queues and build the reply: i32 status followed by
+/// the records of the operation.
+///
+/// SET_PROGRAM(program encoding) replaces the filter and its counters,
+/// CLEAR_PROGRAM removes it. READ_COUNTERS replies with the rule count
+/// u32, then packets u64, bytes u64 and rate limited drops u64 for every
+/// rule and last for the default action.
+pub fn handle_control(filter: &mut Option<PacketFilter>, request: &[u8], queues: u8) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let status = control(filter, request, queues, &mut payload);
+
+    let mut reply = Vec::with_capacity(4 + payload.len());
+    reply.extend_from_slice(&status.to_le_bytes());
+    if status == CTRL_OK {
+        reply.extend_from_slice(&payload);
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::ipv4_frame;
+    use crate::frame::PROTO_UDP;
+    use crate::program::{Action, Rule};
+    use alloc::vec;
+
+    fn request(opcode: u32, payload: &[u8]) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        request.extend_from_slice(payload);
+        request
+    }
+
+    fn status(reply: &[u8]) -> i32 {
+        i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]])
+    }
+
+    #[test]
+    fn test_installs_and_reports_filters() {
+        let mut filter = None;
+        assert_eq!(status(&handle_control(&mut filter, &request(CTRL_READ_COUNTERS, &[]), 1)), CTRL_ENOENT);
+
+        let redirect = Rule { protocol: Some(PROTO_UDP), ..Rule::new(Action::Redirect { queue: 1 }) };
+        let program = FilterProgram::new(vec![redirect], Action::Pass, 2).unwrap().encode();
+        // Verified against the device's queue count
+        assert_eq!(status(&handle_control(&mut filter, &request(CTRL_SET_PROGRAM, &program), 1)), CTRL_EINVAL);
+        assert!(filter.is_none());
+        assert_eq!(status(&handle_control(&mut filter, &request(CTRL_SET_PROGRAM, &program), 2)), CTRL_OK);
+
+        let frame = ipv4_frame(None, PROTO_UDP, [10, 0, 0, 2], [10, 0, 0, 1], (1, 53), 0);
+        filter.as_mut().unwrap().run(&frame, 0);
+        let reply = handle_control(&mut filter, &request(CTRL_READ_COUNTERS, &[]), 2);
+        assert_eq!(status(&reply), CTRL_OK);
+        assert_eq!(reply.len(), 4 + 4 + 2 * 24);
+        assert_eq!(reply[4..8], 1u32.to_le_bytes());
+        assert_eq!(reply[8..16], 1u64.to_le_bytes());
+        assert_eq!(reply[16..24], (frame.len() as u64).to_le_bytes());
+
+        assert_eq!(status(&handle_control(&mut filter, &request(CTRL_CLEAR_PROGRAM, &[]), 2)), CTRL_OK);
+        assert_eq!(status(&handle_control(&mut filter, &request(CTRL_CLEAR_PROGRAM, &[]), 2)), CTRL_ENOENT);
+        assert_eq!(status(&handle_control(&mut filter, &[1, 0], 2)), CTRL_EINVAL);
+    }
+}
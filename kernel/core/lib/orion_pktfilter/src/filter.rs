@@ -0,0 +1,211 @@
+/*
+ * Orion Operating System - Packet Filter
+ *
+ * Runs an installed program against received frames before they are
+ * handed to the network stack. Every rule counts the frames and bytes it
+ * matched; frames falling through to the default action are counted
+ * apart. Rate limit rules keep a token bucket refilled from the caller's
+ * monotonic clock, so the filter itself never reads time.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::frame::FrameInfo;
+use crate::program::{Action, FilterProgram};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Drop,
+    /// Deliver on the given receive queue
+    Redirect(u8),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleCounters {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Matched frames a rate limit dropped
+    pub dropped: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TokenBucket {
+    tokens: u64,
+    /// Time the bucket was last refilled up to
+    last_ns: u64,
+    started: bool,
+}
+
+impl TokenBucket {
+    fn take(&mut self, rate: u32, now_ns: u64) -> bool {
+        let rate = rate as u64;
+        if !self.started {
+            // A fresh bucket starts full
+            self.started = true;
+            self.tokens = rate;
+            self.last_ns = now_ns;
+        }
+        let elapsed = now_ns.saturating_sub(self.last_ns);
+        let refill = ((elapsed as u128 * rate as u128) / NANOS_PER_SEC as u128) as u64;
+        if refill > 0 {
+            self.tokens = (self.tokens + refill).min(rate);
+            // Keep the remainder so slow rates still refill
+            self.last_ns += ((refill as u128 * NANOS_PER_SEC as u128) / rate as u128) as u64;
+        }
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
+
+pub struct PacketFilter {
+    program: FilterProgram,
+    counters: Vec<RuleCounters>,
+    buckets: Vec<TokenBucket>,
+    default_counters: RuleCounters,
+    default_bucket: TokenBucket,
+}
+
+fn apply(action: Action, bucket: &mut TokenBucket, counters: &mut RuleCounters, length: usize, now_ns: u64) -> Verdict {
+    counters.packets += 1;
+    counters.bytes += length as u64;
+    match action {
+        Action::Pass => Verdict::Pass,
+        Action::Drop => Verdict::Drop,
+        Action::Redirect { queue } => Verdict::Redirect(queue),
+        Action::Limit { packets_per_sec } => {
+            if bucket.take(packets_per_sec, now_ns) {
+                Verdict::Pass
+            } else {
+                counters.dropped += 1;
+                Verdict::Drop
+            }
+        }
+    }
+}
+
+impl PacketFilter {
+    pub fn new(program: FilterProgram) -> Self {
+        let rules = program.rules().len();
+        Self {
+            program,
+            counters: alloc::vec![RuleCounters::default(); rules],
+            buckets: alloc::vec![TokenBucket::default(); rules],
+            default_counters: RuleCounters::default(),
+            default_bucket: TokenBucket::default(),
+        }
+    }
+
+    /// Decide the fate of one received frame
+    pub fn run(&mut self, frame: &[u8], now_ns: u64) -> Verdict {
+        let info = FrameInfo::parse(frame);
+        match self.program.classify(&info) {
+            Some(index) => apply(
+                self.program.rules()[index].action,
+                &mut self.buckets[index],
+                &mut self.counters[index],
+                frame.len(),
+                now_ns,
+            ),
+            None => apply(
+                self.program.default_action(),
+                &mut self.default_bucket,
+                &mut self.default_counters,
+                frame.len(),
+                now_ns,
+            ),
+        }
+    }
+
+    pub fn program(&self) -> &FilterProgram {
+        &self.program
+    }
+
+    /// Counters in rule order
+    pub fn counters(&self) -> &[RuleCounters] {
+        &self.counters
+    }
+
+    pub fn default_counters(&self) -> RuleCounters {
+        self.default_counters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::ipv4_frame;
+    use crate::frame::{PROTO_TCP, PROTO_UDP};
+    use crate::program::{Prefix, Rule};
+    use alloc::vec;
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let program = FilterProgram::new(
+            vec![
+                Rule { src: Some(Prefix::ipv4([203, 0, 113, 0], 24)), ..Rule::new(Action::Drop) },
+                Rule {
+                    protocol: Some(PROTO_UDP),
+                    dst_ports: Some((4789, 4789)),
+                    ..Rule::new(Action::Redirect { queue: 1 })
+                },
+                Rule { protocol: Some(PROTO_UDP), ..Rule::new(Action::Pass) },
+            ],
+            Action::Pass,
+            2,
+        )
+        .unwrap();
+        let mut filter = PacketFilter::new(program);
+
+        let attacker = ipv4_frame(None, PROTO_UDP, [203, 0, 113, 9], [10, 0, 0, 1], (1, 4789), 0);
+        let vxlan = ipv4_frame(None, PROTO_UDP, [10, 0, 0, 2], [10, 0, 0, 1], (1, 4789), 0);
+        let dns = ipv4_frame(None, PROTO_UDP, [10, 0, 0, 2], [10, 0, 0, 1], (1, 53), 0);
+        let tcp = ipv4_frame(None, PROTO_TCP, [10, 0, 0, 2], [10, 0, 0, 1], (1, 22), 0x10);
+        assert_eq!(filter.run(&attacker, 0), Verdict::Drop);
+        assert_eq!(filter.run(&attacker, 0), Verdict::Drop);
+        assert_eq!(filter.run(&vxlan, 0), Verdict::Redirect(1));
+        assert_eq!(filter.run(&dns, 0), Verdict::Pass);
+        assert_eq!(filter.run(&tcp, 0), Verdict::Pass);
+
+        let counters = filter.counters();
+        assert_eq!((counters[0].packets, counters[0].bytes), (2, 2 * attacker.len() as u64));
+        assert_eq!((counters[1].packets, counters[2].packets), (1, 1));
+        assert_eq!(filter.default_counters().packets, 1);
+    }
+
+    #[test]
+    fn test_limit_rules_use_token_buckets() {
+        let rule = Rule { protocol: Some(PROTO_TCP), tcp_flags: Some((0x02, 0x02)), ..Rule::new(Action::Drop) };
+        let limit = Rule { action: Action::Limit { packets_per_sec: 4 }, ..rule };
+        let mut filter = PacketFilter::new(FilterProgram::new(vec![limit], Action::Pass, 1).unwrap());
+        let syn = ipv4_frame(None, PROTO_TCP, [10, 0, 0, 2], [10, 0, 0, 1], (1, 80), 0x02);
+
+        // A full bucket's worth passes, then the excess drops
+        let verdicts: Vec<_> = (0..6).map(|_| filter.run(&syn, 1_000)).collect();
+        assert_eq!(verdicts[..4], [Verdict::Pass; 4]);
+        assert_eq!(verdicts[4..], [Verdict::Drop; 2]);
+
+        // A quarter second later one token is back, and not more
+        assert_eq!(filter.run(&syn, 1_000 + NANOS_PER_SEC / 4), Verdict::Pass);
+        assert_eq!(filter.run(&syn, 1_000 + NANOS_PER_SEC / 4), Verdict::Drop);
+
+        // Long idle periods refill only up to the rate
+        let later = 1_000 + 10 * NANOS_PER_SEC;
+        let passed = (0..10).filter(|_| filter.run(&syn, later) == Verdict::Pass).count();
+        assert_eq!(passed, 4);
+
+        let counters = filter.counters()[0];
+        assert_eq!((counters.packets, counters.dropped), (18, 9));
+    }
+}
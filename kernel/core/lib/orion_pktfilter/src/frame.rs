@@ -0,0 +1,196 @@
+/*
+ * Orion Operating System - Frame Header Parsing
+ *
+ * Just enough of Ethernet, 802.1Q, IPv4, IPv6, TCP, UDP and ICMP to match
+ * filter rules against. Parsing never fails: whatever cannot be read is
+ * left unset, and rules that need it do not match. IPv4 addresses are
+ * kept in their IPv4-mapped IPv6 form so one prefix type covers both.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+pub const ETHERTYPE_QINQ: u16 = 0x88A8;
+pub const ETHERTYPE_IPV6: u16 = 0x86DD;
+
+pub const PROTO_ICMP: u8 = 1;
+pub const PROTO_TCP: u8 = 6;
+pub const PROTO_UDP: u8 = 17;
+pub const PROTO_ICMPV6: u8 = 58;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub length: usize,
+    /// Ethertype after any VLAN tags
+    pub ethertype: u16,
+    pub vlan: Option<u16>,
+    pub protocol: Option<u8>,
+    pub src: Option<[u8; 16]>,
+    pub dst: Option<[u8; 16]>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    pub tcp_flags: Option<u8>,
+}
+
+fn be16(frame: &[u8], offset: usize) -> Option<u16> {
+    let bytes = frame.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn ipv4_mapped(bytes: &[u8]) -> [u8; 16] {
+    let mut address = [0u8; 16];
+    address[10] = 0xFF;
+    address[11] = 0xFF;
+    address[12..].copy_from_slice(&bytes[..4]);
+    address
+}
+
+impl FrameInfo {
+    pub fn parse(frame: &[u8]) -> Self {
+        let mut info = FrameInfo { length: frame.len(), ..FrameInfo::default() };
+        let mut offset = 12;
+        let mut ethertype = match be16(frame, offset) {
+            Some(ethertype) => ethertype,
+            None => return info,
+        };
+        while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+            // The innermost tag is the one that names the VLAN
+            match (be16(frame, offset + 2), be16(frame, offset + 4)) {
+                (Some(tci), Some(next)) => {
+                    info.vlan = Some(tci & 0x0FFF);
+                    ethertype = next;
+                    offset += 4;
+                }
+                _ => return info,
+            }
+        }
+        info.ethertype = ethertype;
+
+        let network = offset + 2;
+        let transport = match ethertype {
+            ETHERTYPE_IPV4 => info.parse_ipv4(frame, network),
+            ETHERTYPE_IPV6 => info.parse_ipv6(frame, network),
+            _ => None,
+        };
+        if let Some(transport) = transport {
+            info.parse_transport(frame, transport);
+        }
+        info
+    }
+
+    fn parse_ipv4(&mut self, frame: &[u8], offset: usize) -> Option<usize> {
+        let header = frame.get(offset..offset + 20)?;
+        let header_len = (header[0] & 0x0F) as usize * 4;
+        if header[0] >> 4 != 4 || header_len < 20 {
+            return None;
+        }
+        self.protocol = Some(header[9]);
+        self.src = Some(ipv4_mapped(&header[12..16]));
+        self.dst = Some(ipv4_mapped(&header[16..20]));
+        // Only the first fragment carries the transport header
+        let fragment_offset = u16::from_be_bytes([header[6], header[7]]) & 0x1FFF;
+        (fragment_offset == 0).then_some(offset + header_len)
+    }
+
+    fn parse_ipv6(&mut self, frame: &[u8], offset: usize) -> Option<usize> {
+        let header = frame.get(offset..offset + 40)?;
+        if header[0] >> 4 != 6 {
+            return None;
+        }
+        let mut src = [0u8; 16];
+        let mut dst = [0u8; 16];
+        src.copy_from_slice(&header[8..24]);
+        dst.copy_from_slice(&header[24..40]);
+        self.src = Some(src);
+        self.dst = Some(dst);
+        // Extension headers are not walked: the next header is taken as is
+        self.protocol = Some(header[6]);
+        Some(offset + 40)
+    }
+
+    fn parse_transport(&mut self, frame: &[u8], offset: usize) {
+        match self.protocol {
+            Some(PROTO_TCP) | Some(PROTO_UDP) => {
+                self.src_port = be16(frame, offset);
+                self.dst_port = be16(frame, offset + 2);
+                if self.protocol == Some(PROTO_TCP) {
+                    self.tcp_flags = frame.get(offset + 13).copied();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Ethernet + IPv4 + TCP or UDP frame, optionally VLAN tagged
+    pub(crate) fn ipv4_frame(
+        vlan: Option<u16>,
+        protocol: u8,
+        src: [u8; 4],
+        dst: [u8; 4],
+        ports: (u16, u16),
+        tcp_flags: u8,
+    ) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&[0xFF; 12]);
+        if let Some(vlan) = vlan {
+            frame.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            frame.extend_from_slice(&vlan.to_be_bytes());
+        }
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        let mut ip = [0u8; 20];
+        ip[0] = 0x45;
+        ip[9] = protocol;
+        ip[12..16].copy_from_slice(&src);
+        ip[16..20].copy_from_slice(&dst);
+        frame.extend_from_slice(&ip);
+        let mut transport = [0u8; 20];
+        transport[0..2].copy_from_slice(&ports.0.to_be_bytes());
+        transport[2..4].copy_from_slice(&ports.1.to_be_bytes());
+        transport[13] = tcp_flags;
+        frame.extend_from_slice(&transport);
+        frame
+    }
+
+    #[test]
+    fn test_parses_headers() {
+        let frame = ipv4_frame(Some(42), PROTO_TCP, [10, 0, 0, 1], [10, 0, 0, 2], (40000, 443), 0x02);
+        let info = FrameInfo::parse(&frame);
+        assert_eq!((info.ethertype, info.vlan, info.protocol), (ETHERTYPE_IPV4, Some(42), Some(PROTO_TCP)));
+        assert_eq!(info.src.unwrap()[10..], [0xFF, 0xFF, 10, 0, 0, 1]);
+        assert_eq!((info.src_port, info.dst_port, info.tcp_flags), (Some(40000), Some(443), Some(0x02)));
+
+        // A later fragment has no transport header
+        let mut fragment = ipv4_frame(None, PROTO_UDP, [1, 1, 1, 1], [2, 2, 2, 2], (53, 53), 0);
+        fragment[14 + 7] = 0x10;
+        let info = FrameInfo::parse(&fragment);
+        assert_eq!((info.protocol, info.dst_port), (Some(PROTO_UDP), None));
+
+        let mut ipv6 = Vec::from([0u8; 12]);
+        ipv6.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+        let mut header = [0u8; 40];
+        header[0] = 0x60;
+        header[6] = PROTO_UDP;
+        header[39] = 1;
+        ipv6.extend_from_slice(&header);
+        ipv6.extend_from_slice(&[0x12, 0x34, 0x00, 0x35, 0, 0, 0, 0]);
+        let info = FrameInfo::parse(&ipv6);
+        assert_eq!((info.dst.unwrap()[15], info.src_port, info.dst_port), (1, Some(0x1234), Some(53)));
+
+        // Truncated frames keep what could be read
+        let info = FrameInfo::parse(&frame[..30]);
+        assert_eq!((info.ethertype, info.protocol), (ETHERTYPE_IPV4, None));
+        assert_eq!(FrameInfo::parse(&[0u8; 4]).ethertype, 0);
+    }
+}
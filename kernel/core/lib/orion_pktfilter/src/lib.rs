@@ -0,0 +1,33 @@
+/*
+ * Orion Operating System - Early Packet Filter
+ *
+ * Filter programs run by NIC drivers on every received frame before it
+ * is handed to the network server: a table of match rules, each dropping,
+ * rate limiting, passing or redirecting the frames it matches to a given
+ * receive queue. Programs are verified when loaded, so the receive path
+ * runs them without further checks, and every rule counts what it hit.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod control;
+pub mod filter;
+pub mod frame;
+pub mod multicast;
+pub mod program;
+pub mod rx_mode;
+
+pub use control::{handle_control, FILTER_IOCTL_CONTROL};
+pub use filter::{PacketFilter, RuleCounters, Verdict};
+pub use frame::FrameInfo;
+pub use multicast::{handle_multicast, MulticastFilter, FILTER_IOCTL_MULTICAST};
+pub use program::{Action, FilterError, FilterProgram, Prefix, Rule};
+pub use rx_mode::{handle_rx_mode, RxMode, RxModeChange, VlanTable, FILTER_IOCTL_RX_MODE};
@@ -0,0 +1,180 @@
+/*
+ * Orion Operating System - Multicast Address Filter
+ *
+ * Link-layer multicast groups a NIC accepts, programmed by the network
+ * server as the host joins and leaves groups. Drivers with an exact or
+ * hash filter in hardware load it from here; the others check frames in
+ * their receive path. Unicast and broadcast frames are never filtered, and
+ * a list too long to hold falls back to accepting every multicast frame.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::control::{CTRL_EINVAL, CTRL_OK};
+
+/// Ioctl carrying a multicast list; the reply is an i32 status
+pub const FILTER_IOCTL_MULTICAST: u32 = 0x2011;
+
+/// Count in a multicast request asking for every multicast frame
+pub const MULTICAST_ALL: u32 = u32::MAX;
+
+/// Addresses held before falling back to accepting all multicast
+pub const MAX_MULTICAST_ADDRESSES: usize = 64;
+
+const BROADCAST: [u8; 6] = [0xFF; 6];
+
+/// Big-endian Ethernet CRC-32 of an address, as NIC hash filters use it
+pub fn ether_crc(address: &[u8; 6]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in address {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let carry = (crc >> 31) ^ (byte as u32 & 1);
+            crc <<= 1;
+            byte >>= 1;
+            if carry != 0 {
+                crc ^= 0x04C1_1DB7;
+            }
+        }
+    }
+    crc
+}
+
+/// Multicast addresses accepted by a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MulticastFilter {
+    addresses: Vec<[u8; 6]>,
+    all: bool,
+}
+
+impl Default for MulticastFilter {
+    /// Every multicast frame is accepted until a list is programmed
+    fn default() -> Self {
+        Self { addresses: Vec::new(), all: true }
+    }
+}
+
+impl MulticastFilter {
+    /// Accept only the given groups, or all of them past MAX_MULTICAST_ADDRESSES
+    pub fn set(&mut self, addresses: &[[u8; 6]]) {
+        self.addresses.clear();
+        self.all = addresses.len() > MAX_MULTICAST_ADDRESSES;
+        if !self.all {
+            for address in addresses {
+                if !self.addresses.contains(address) {
+                    self.addresses.push(*address);
+                }
+            }
+        }
+    }
+
+    /// Accept every multicast frame
+    pub fn set_all(&mut self) {
+        self.addresses.clear();
+        self.all = true;
+    }
+
+    pub fn accepts_all(&self) -> bool {
+        self.all
+    }
+
+    pub fn addresses(&self) -> &[[u8; 6]] {
+        &self.addresses
+    }
+
+    /// Whether a frame passes: anything but a multicast frame to a group not in the list
+    pub fn accepts(&self, frame: &[u8]) -> bool {
+        let destination: [u8; 6] = match frame.get(0..6) {
+            Some(bytes) => [bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]],
+            None => return false,
+        };
+        if destination[0] & 1 == 0 || destination == BROADCAST || self.all {
+            return true;
+        }
+        self.addresses.contains(&destination)
+    }
+
+    /// 64-bit hash filter (MAR registers, low word first) selecting bit
+    /// `ether_crc >> 26` for every address
+    pub fn hash_table(&self) -> [u32; 2] {
+        if self.all {
+            return [u32::MAX; 2];
+        }
+        let mut table = [0u32; 2];
+        for address in &self.addresses {
+            let bit = ether_crc(address) >> 26;
+            table[(bit >> 5) as usize] |= 1 << (bit & 31);
+        }
+        table
+    }
+}
+
+/// Serve a multicast request and build the i32 status reply: count u32
+/// (MULTICAST_ALL for every group) then that many 6-byte addresses.
+pub fn handle_multicast(filter: &mut MulticastFilter, request: &[u8]) -> Vec<u8> {
+    let status = match request.get(0..4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])) {
+        Some(MULTICAST_ALL) if request.len() == 4 => {
+            filter.set_all();
+            CTRL_OK
+        }
+        Some(count) if (count as usize).checked_mul(6) == Some(request.len() - 4) => {
+            let addresses: Vec<[u8; 6]> = request[4..]
+                .chunks_exact(6)
+                .map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5]])
+                .collect();
+            filter.set(&addresses);
+            CTRL_OK
+        }
+        _ => CTRL_EINVAL,
+    };
+    status.to_le_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MDNS: [u8; 6] = [0x01, 0x00, 0x5E, 0x00, 0x00, 0xFB];
+    const SSDP: [u8; 6] = [0x01, 0x00, 0x5E, 0x7F, 0xFF, 0xFA];
+
+    fn frame(destination: [u8; 6]) -> Vec<u8> {
+        let mut frame = destination.to_vec();
+        frame.resize(60, 0);
+        frame
+    }
+
+    #[test]
+    fn test_filters_multicast_groups() {
+        let mut filter = MulticastFilter::default();
+        assert!(filter.accepts(&frame(SSDP)));
+        assert_eq!(filter.hash_table(), [u32::MAX; 2]);
+
+        let mut request = 1u32.to_le_bytes().to_vec();
+        request.extend_from_slice(&MDNS);
+        assert_eq!(handle_multicast(&mut filter, &request), CTRL_OK.to_le_bytes());
+        assert!(filter.accepts(&frame(MDNS)));
+        assert!(!filter.accepts(&frame(SSDP)));
+        // Unicast and broadcast are not the filter's business
+        assert!(filter.accepts(&frame([0x52, 0x54, 0, 0x12, 0x34, 0x56])));
+        assert!(filter.accepts(&frame(BROADCAST)));
+
+        let bit = ether_crc(&MDNS) >> 26;
+        let table = filter.hash_table();
+        assert_eq!(table[0].count_ones() + table[1].count_ones(), 1);
+        assert_ne!(table[(bit >> 5) as usize] & (1 << (bit & 31)), 0);
+
+        assert_eq!(handle_multicast(&mut filter, &request[..7]), CTRL_EINVAL.to_le_bytes());
+        assert_eq!(handle_multicast(&mut filter, &MULTICAST_ALL.to_le_bytes()), CTRL_OK.to_le_bytes());
+        assert!(filter.accepts(&frame(SSDP)));
+
+        let many: Vec<[u8; 6]> = (0..=MAX_MULTICAST_ADDRESSES as u8).map(|i| [0x01, 0x00, 0x5E, 0, 0, i]).collect();
+        filter.set(&many);
+        assert!(filter.accepts_all());
+    }
+}
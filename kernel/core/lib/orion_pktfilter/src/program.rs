@@ -0,0 +1,477 @@
+/*
+ * Orion Operating System - Filter Programs
+ *
+ * A program is an ordered rule table and a default action; the first
+ * rule matching a frame decides its fate. Programs travel over IPC in a
+ * fixed little-endian encoding:
+ *
+ *   header  rule_count:u32 action:u8 queue:u8 reserved:u16 rate:u32
+ *           reserved:u32 (the default action)
+ *   rule    fields:u16 action:u8 queue:u8 rate:u32 ethertype:u16
+ *           vlan:u16 protocol:u8 tcp_mask:u8 tcp_value:u8 src_len:u8
+ *           dst_len:u8 reserved[3] src_ports:u16x2 dst_ports:u16x2
+ *           length:u16x2 src[16] dst[16]
+ *
+ * `fields` says which matchers a rule uses; IPv4 prefixes are given in
+ * their IPv4-mapped IPv6 form. Verification happens once, when the
+ * program is built or decoded: unknown fields or actions, prefixes past
+ * 128 bits, inverted ranges, port matches without TCP or UDP, redirects
+ * to queues the device does not have and zero rates are all refused.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::frame::{FrameInfo, PROTO_TCP, PROTO_UDP};
+
+/// Most rules in one program
+pub const MAX_RULES: usize = 64;
+
+pub const PROGRAM_HEADER_SIZE: usize = 16;
+pub const RULE_SIZE: usize = 64;
+
+// Rule fields
+pub const FIELD_ETHERTYPE: u16 = 1 << 0;
+pub const FIELD_VLAN: u16 = 1 << 1;
+pub const FIELD_PROTOCOL: u16 = 1 << 2;
+pub const FIELD_SRC: u16 = 1 << 3;
+pub const FIELD_DST: u16 = 1 << 4;
+pub const FIELD_SRC_PORTS: u16 = 1 << 5;
+pub const FIELD_DST_PORTS: u16 = 1 << 6;
+pub const FIELD_TCP_FLAGS: u16 = 1 << 7;
+pub const FIELD_LENGTH: u16 = 1 << 8;
+const FIELDS_KNOWN: u16 = (1 << 9) - 1;
+
+// Action codes
+const ACTION_PASS: u8 = 0;
+const ACTION_DROP: u8 = 1;
+const ACTION_REDIRECT: u8 = 2;
+const ACTION_LIMIT: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterError {
+    TooManyRules,
+    /// Encoding shorter or longer than its rule count says
+    Truncated,
+    UnknownField,
+    UnknownAction,
+    BadPrefix,
+    /// Port or length range with its low bound above the high one
+    BadRange,
+    /// Port or TCP flag match on a rule not restricted to TCP or UDP
+    NeedsProtocol,
+    /// Redirect to a queue the device does not have
+    BadQueue,
+    BadRate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Pass,
+    Drop,
+    /// Deliver on the given receive queue
+    Redirect {
+        queue: u8,
+    },
+    /// Pass up to the given rate, with a one second burst, drop the rest
+    Limit {
+        packets_per_sec: u32,
+    },
+}
+
+impl Action {
+    /// (code, queue, rate) as laid out in the encoding
+    fn parts(&self) -> (u8, u8, u32) {
+        match *self {
+            Action::Pass => (ACTION_PASS, 0, 0),
+            Action::Drop => (ACTION_DROP, 0, 0),
+            Action::Redirect { queue } => (ACTION_REDIRECT, queue, 0),
+            Action::Limit { packets_per_sec } => (ACTION_LIMIT, 0, packets_per_sec),
+        }
+    }
+
+    fn from_parts(code: u8, queue: u8, rate: u32) -> Result<Self, FilterError> {
+        match code {
+            ACTION_PASS => Ok(Action::Pass),
+            ACTION_DROP => Ok(Action::Drop),
+            ACTION_REDIRECT => Ok(Action::Redirect { queue }),
+            ACTION_LIMIT => Ok(Action::Limit { packets_per_sec: rate }),
+            _ => Err(FilterError::UnknownAction),
+        }
+    }
+
+    fn verify(&self, queues: u8) -> Result<(), FilterError> {
+        match *self {
+            Action::Redirect { queue } if queue >= queues => Err(FilterError::BadQueue),
+            Action::Limit { packets_per_sec: 0 } => Err(FilterError::BadRate),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prefix {
+    pub address: [u8; 16],
+    /// Leading bits that must match
+    pub length: u8,
+}
+
+impl Prefix {
+    pub fn ipv4(address: [u8; 4], length: u8) -> Self {
+        let mut mapped = [0u8; 16];
+        mapped[10] = 0xFF;
+        mapped[11] = 0xFF;
+        mapped[12..].copy_from_slice(&address);
+        Self { address: mapped, length: length.saturating_add(96) }
+    }
+
+    pub fn ipv6(address: [u8; 16], length: u8) -> Self {
+        Self { address, length }
+    }
+
+    pub fn contains(&self, address: &[u8; 16]) -> bool {
+        let bytes = (self.length / 8) as usize;
+        let bits = self.length % 8;
+        if self.address[..bytes] != address[..bytes] {
+            return false;
+        }
+        bits == 0 || {
+            let mask = 0xFFu8 << (8 - bits);
+            self.address[bytes] & mask == address[bytes] & mask
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub ethertype: Option<u16>,
+    pub vlan: Option<u16>,
+    pub protocol: Option<u8>,
+    pub src: Option<Prefix>,
+    pub dst: Option<Prefix>,
+    pub src_ports: Option<(u16, u16)>,
+    pub dst_ports: Option<(u16, u16)>,
+    /// (mask, value): the masked TCP flags must equal the value
+    pub tcp_flags: Option<(u8, u8)>,
+    pub length: Option<(u16, u16)>,
+    pub action: Action,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn in_range(value: Option<u16>, range: Option<(u16, u16)>) -> bool {
+    match (range, value) {
+        (None, _) => true,
+        (Some((low, high)), Some(value)) => low <= value && value <= high,
+        (Some(_), None) => false,
+    }
+}
+
+fn in_prefix(address: Option<[u8; 16]>, prefix: Option<Prefix>) -> bool {
+    match (prefix, address) {
+        (None, _) => true,
+        (Some(prefix), Some(address)) => prefix.contains(&address),
+        (Some(_), None) => false,
+    }
+}
+
+impl Rule {
+    /// A rule matching every frame
+    pub fn new(action: Action) -> Self {
+        Self {
+            ethertype: None,
+            vlan: None,
+            protocol: None,
+            src: None,
+            dst: None,
+            src_ports: None,
+            dst_ports: None,
+            tcp_flags: None,
+            length: None,
+            action,
+        }
+    }
+
+    pub fn matches(&self, frame: &FrameInfo) -> bool {
+        self.ethertype.is_none_or(|ethertype| ethertype == frame.ethertype)
+            && self.vlan.is_none_or(|vlan| frame.vlan == Some(vlan))
+            && self.protocol.is_none_or(|protocol| frame.protocol == Some(protocol))
+            && in_prefix(frame.src, self.src)
+            && in_prefix(frame.dst, self.dst)
+            && in_range(frame.src_port, self.src_ports)
+            && in_range(frame.dst_port, self.dst_ports)
+            && self.tcp_flags.is_none_or(|(mask, value)| frame.tcp_flags.is_some_and(|flags| flags & mask == value))
+            && in_range(Some(frame.length.min(u16::MAX as usize) as u16), self.length)
+    }
+
+    fn verify(&self, queues: u8) -> Result<(), FilterError> {
+        self.action.verify(queues)?;
+        if [self.src, self.dst].iter().flatten().any(|prefix| prefix.length > 128) {
+            return Err(FilterError::BadPrefix);
+        }
+        if [self.src_ports, self.dst_ports, self.length].iter().flatten().any(|(low, high)| low > high) {
+            return Err(FilterError::BadRange);
+        }
+        let ports = self.src_ports.is_some() || self.dst_ports.is_some();
+        let transport = matches!(self.protocol, Some(PROTO_TCP) | Some(PROTO_UDP));
+        if (ports && !transport) || (self.tcp_flags.is_some() && self.protocol != Some(PROTO_TCP)) {
+            return Err(FilterError::NeedsProtocol);
+        }
+        Ok(())
+    }
+
+    fn encode(&self) -> [u8; RULE_SIZE] {
+        let mut bytes = [0u8; RULE_SIZE];
+        let mut fields = 0;
+        let (action, queue, rate) = self.action.parts();
+        bytes[2] = action;
+        bytes[3] = queue;
+        bytes[4..8].copy_from_slice(&rate.to_le_bytes());
+
+        if let Some(ethertype) = self.ethertype {
+            fields |= FIELD_ETHERTYPE;
+            bytes[8..10].copy_from_slice(&ethertype.to_le_bytes());
+        }
+        if let Some(vlan) = self.vlan {
+            fields |= FIELD_VLAN;
+            bytes[10..12].copy_from_slice(&vlan.to_le_bytes());
+        }
+        if let Some(protocol) = self.protocol {
+            fields |= FIELD_PROTOCOL;
+            bytes[12] = protocol;
+        }
+        if let Some((mask, value)) = self.tcp_flags {
+            fields |= FIELD_TCP_FLAGS;
+            bytes[13] = mask;
+            bytes[14] = value;
+        }
+        if let Some(src) = self.src {
+            fields |= FIELD_SRC;
+            bytes[15] = src.length;
+            bytes[32..48].copy_from_slice(&src.address);
+        }
+        if let Some(dst) = self.dst {
+            fields |= FIELD_DST;
+            bytes[16] = dst.length;
+            bytes[48..64].copy_from_slice(&dst.address);
+        }
+        for (field, range, offset) in [
+            (FIELD_SRC_PORTS, self.src_ports, 20),
+            (FIELD_DST_PORTS, self.dst_ports, 24),
+            (FIELD_LENGTH, self.length, 28),
+        ] {
+            if let Some((low, high)) = range {
+                fields |= field;
+                bytes[offset..offset + 2].copy_from_slice(&low.to_le_bytes());
+                bytes[offset + 2..offset + 4].copy_from_slice(&high.to_le_bytes());
+            }
+        }
+        bytes[0..2].copy_from_slice(&fields.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, FilterError> {
+        let fields = read_u16(bytes, 0);
+        if fields & !FIELDS_KNOWN != 0 {
+            return Err(FilterError::UnknownField);
+        }
+        let has = |field: u16| fields & field != 0;
+        let range =
+            |field: u16, offset: usize| has(field).then(|| (read_u16(bytes, offset), read_u16(bytes, offset + 2)));
+        let prefix = |field: u16, length: usize, offset: usize| {
+            has(field).then(|| {
+                let mut address = [0u8; 16];
+                address.copy_from_slice(&bytes[offset..offset + 16]);
+                Prefix { address, length: bytes[length] }
+            })
+        };
+        Ok(Self {
+            ethertype: has(FIELD_ETHERTYPE).then(|| read_u16(bytes, 8)),
+            vlan: has(FIELD_VLAN).then(|| read_u16(bytes, 10)),
+            protocol: has(FIELD_PROTOCOL).then_some(bytes[12]),
+            tcp_flags: has(FIELD_TCP_FLAGS).then_some((bytes[13], bytes[14])),
+            src: prefix(FIELD_SRC, 15, 32),
+            dst: prefix(FIELD_DST, 16, 48),
+            src_ports: range(FIELD_SRC_PORTS, 20),
+            dst_ports: range(FIELD_DST_PORTS, 24),
+            length: range(FIELD_LENGTH, 28),
+            action: Action::from_parts(bytes[2], bytes[3], read_u32(bytes, 4))?,
+        })
+    }
+}
+
+/// A verified rule table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterProgram {
+    rules: Vec<Rule>,
+    default: Action,
+}
+
+impl FilterProgram {
+    /// Verify a program for a device with `queues` receive queues
+    pub fn new(rules: Vec<Rule>, default: Action, queues: u8) -> Result<Self, FilterError> {
+        if rules.len() > MAX_RULES {
+            return Err(FilterError::TooManyRules);
+        }
+        default.verify(queues)?;
+        for rule in &rules {
+            rule.verify(queues)?;
+        }
+        Ok(Self { rules, default })
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn default_action(&self) -> Action {
+        self.default
+    }
+
+    /// Index of the first rule matching the frame, None for the default
+    pub fn classify(&self, frame: &FrameInfo) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.matches(frame))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PROGRAM_HEADER_SIZE + self.rules.len() * RULE_SIZE);
+        let mut header = [0u8; PROGRAM_HEADER_SIZE];
+        header[0..4].copy_from_slice(&(self.rules.len() as u32).to_le_bytes());
+        let (action, queue, rate) = self.default.parts();
+        header[4] = action;
+        header[5] = queue;
+        header[8..12].copy_from_slice(&rate.to_le_bytes());
+        out.extend_from_slice(&header);
+        for rule in &self.rules {
+            out.extend_from_slice(&rule.encode());
+        }
+        out
+    }
+
+    /// Decode and verify a program for a device with `queues` receive queues
+    pub fn decode(bytes: &[u8], queues: u8) -> Result<Self, FilterError> {
+        if bytes.len() < PROGRAM_HEADER_SIZE {
+            return Err(FilterError::Truncated);
+        }
+        let count = read_u32(bytes, 0) as usize;
+        if count > MAX_RULES {
+            return Err(FilterError::TooManyRules);
+        }
+        if bytes.len() != PROGRAM_HEADER_SIZE + count * RULE_SIZE {
+            return Err(FilterError::Truncated);
+        }
+        let default = Action::from_parts(bytes[4], bytes[5], read_u32(bytes, 8))?;
+        let rules = bytes[PROGRAM_HEADER_SIZE..].chunks_exact(RULE_SIZE).map(Rule::decode).collect::<Result<_, _>>()?;
+        Self::new(rules, default, queues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::ipv4_frame;
+    use crate::frame::{ETHERTYPE_IPV4, PROTO_ICMP};
+    use alloc::vec;
+
+    fn syn_flood_rule() -> Rule {
+        Rule {
+            protocol: Some(PROTO_TCP),
+            dst: Some(Prefix::ipv4([192, 168, 1, 0], 24)),
+            dst_ports: Some((80, 443)),
+            tcp_flags: Some((0x12, 0x02)),
+            ..Rule::new(Action::Limit { packets_per_sec: 1000 })
+        }
+    }
+
+    #[test]
+    fn test_rules_match_frames() {
+        let rule = syn_flood_rule();
+        let syn = ipv4_frame(None, PROTO_TCP, [10, 0, 0, 1], [192, 168, 1, 7], (5555, 443), 0x02);
+        let ack = ipv4_frame(None, PROTO_TCP, [10, 0, 0, 1], [192, 168, 1, 7], (5555, 443), 0x10);
+        let other_net = ipv4_frame(None, PROTO_TCP, [10, 0, 0, 1], [192, 168, 2, 7], (5555, 443), 0x02);
+        assert!(rule.matches(&FrameInfo::parse(&syn)));
+        assert!(!rule.matches(&FrameInfo::parse(&ack)));
+        assert!(!rule.matches(&FrameInfo::parse(&other_net)));
+
+        let vlan = Rule { vlan: Some(7), ethertype: Some(ETHERTYPE_IPV4), ..Rule::new(Action::Drop) };
+        let tagged = ipv4_frame(Some(7), PROTO_ICMP, [1, 2, 3, 4], [5, 6, 7, 8], (0, 0), 0);
+        assert!(vlan.matches(&FrameInfo::parse(&tagged)));
+        assert!(!vlan.matches(&FrameInfo::parse(&syn)));
+
+        let short = Rule { length: Some((0, 53)), ..Rule::new(Action::Drop) };
+        assert!(short.matches(&FrameInfo::parse(&syn[..40])));
+        assert!(!short.matches(&FrameInfo::parse(&syn)));
+
+        assert!(Prefix::ipv6([0x20, 0x01, 0x0d, 0xb8, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 33)
+            .contains(&[0x20, 0x01, 0x0d, 0xb8, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]));
+        assert!(!Prefix::ipv6([0x20, 0x01, 0x0d, 0xb8, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 33)
+            .contains(&[0x20, 0x01, 0x0d, 0xb8, 0x7F, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn test_programs_are_verified() {
+        let redirect = Rule { protocol: Some(PROTO_UDP), ..Rule::new(Action::Redirect { queue: 3 }) };
+        assert_eq!(FilterProgram::new(vec![redirect], Action::Pass, 2), Err(FilterError::BadQueue));
+        assert!(FilterProgram::new(vec![redirect], Action::Pass, 4).is_ok());
+
+        let checks = [
+            (Rule { dst_ports: Some((80, 80)), ..Rule::new(Action::Drop) }, FilterError::NeedsProtocol),
+            (
+                Rule { protocol: Some(PROTO_UDP), tcp_flags: Some((2, 2)), ..Rule::new(Action::Drop) },
+                FilterError::NeedsProtocol,
+            ),
+            (Rule { length: Some((100, 50)), ..Rule::new(Action::Drop) }, FilterError::BadRange),
+            (Rule { src: Some(Prefix::ipv6([0; 16], 129)), ..Rule::new(Action::Drop) }, FilterError::BadPrefix),
+            (Rule::new(Action::Limit { packets_per_sec: 0 }), FilterError::BadRate),
+        ];
+        for (rule, error) in checks {
+            assert_eq!(FilterProgram::new(vec![rule], Action::Pass, 1), Err(error));
+        }
+        assert_eq!(
+            FilterProgram::new(vec![Rule::new(Action::Drop); MAX_RULES + 1], Action::Pass, 1),
+            Err(FilterError::TooManyRules)
+        );
+    }
+
+    #[test]
+    fn test_programs_round_trip() {
+        let rules = vec![
+            syn_flood_rule(),
+            Rule {
+                ethertype: Some(ETHERTYPE_IPV4),
+                vlan: Some(12),
+                protocol: Some(PROTO_UDP),
+                src: Some(Prefix::ipv4([10, 1, 0, 0], 16)),
+                src_ports: Some((53, 53)),
+                length: Some((64, 1518)),
+                ..Rule::new(Action::Redirect { queue: 1 })
+            },
+            Rule::new(Action::Drop),
+        ];
+        let program = FilterProgram::new(rules, Action::Pass, 2).unwrap();
+        let encoded = program.encode();
+        assert_eq!(encoded.len(), PROGRAM_HEADER_SIZE + 3 * RULE_SIZE);
+        assert_eq!(FilterProgram::decode(&encoded, 2), Ok(program));
+
+        // Decoding verifies just like building
+        assert_eq!(FilterProgram::decode(&encoded, 1), Err(FilterError::BadQueue));
+        assert_eq!(FilterProgram::decode(&encoded[..encoded.len() - 1], 2), Err(FilterError::Truncated));
+        let mut unknown = encoded.clone();
+        unknown[PROGRAM_HEADER_SIZE + 1] = 0x80;
+        assert_eq!(FilterProgram::decode(&unknown, 2), Err(FilterError::UnknownField));
+        let mut bad_action = encoded;
+        bad_action[PROGRAM_HEADER_SIZE + 2] = 9;
+        assert_eq!(FilterProgram::decode(&bad_action, 2), Err(FilterError::UnknownAction));
+    }
+}
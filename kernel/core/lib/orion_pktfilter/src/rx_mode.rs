@@ -0,0 +1,392 @@
+/*
+ * Orion Operating System - Receive Mode Control
+ *
+ * Station settings the network manager applies to a NIC: promiscuous
+ * and all-multicast reception, the station MAC address, the VLAN filter
+ * table and the IPv4 addresses to announce when the device moved, after
+ * a live migration for instance. Requests start with a 32-bit opcode and
+ * replies with an i32 status; all fields are little-endian:
+ *
+ *   GET            (empty)          -> flags:u32 mac[6] pad[2] vlans:u32 vid:u16[vlans]
+ *   SET_FLAGS      flags:u32        -> (empty)
+ *   SET_MAC        mac[6]           -> (empty)
+ *   ADD_VLAN       vid:u16          -> (empty)
+ *   REMOVE_VLAN    vid:u16          -> (empty)
+ *   SET_ANNOUNCE   count:u32 ipv4:u32[count] -> (empty)
+ *
+ * The driver applies each change to the hardware before it is recorded,
+ * so a device refusing it leaves the mode as it was.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::control::{CTRL_EINVAL, CTRL_ENOENT, CTRL_OK};
+
+/// Ioctl carrying a receive mode request; the reply is sent back as data
+pub const FILTER_IOCTL_RX_MODE: u32 = 0x2012;
+
+// Receive mode opcodes
+pub const RX_MODE_GET: u32 = 1;
+pub const RX_MODE_SET_FLAGS: u32 = 2;
+pub const RX_MODE_SET_MAC: u32 = 3;
+pub const RX_MODE_ADD_VLAN: u32 = 4;
+pub const RX_MODE_REMOVE_VLAN: u32 = 5;
+pub const RX_MODE_SET_ANNOUNCE: u32 = 6;
+
+// Flags of SET_FLAGS and GET
+pub const RX_FLAG_PROMISCUOUS: u32 = 1 << 0;
+pub const RX_FLAG_ALL_MULTICAST: u32 = 1 << 1;
+
+/// VLAN IDs 1 to 4094 can be filtered; 0 tags priority only
+pub const MAX_VLAN_ID: u16 = 4094;
+pub const MAX_ANNOUNCE_ADDRESSES: usize = 16;
+
+/// Size of the announce frames, padding excluded
+pub const ANNOUNCE_FRAME_SIZE: usize = 42;
+
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_RARP: u16 = 0x8035;
+
+/// A change the driver has to make to the hardware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxModeChange {
+    Flags { promiscuous: bool, all_multicast: bool },
+    Mac([u8; 6]),
+    AddVlan(u16),
+    RemoveVlan(u16),
+}
+
+/// Set of VLAN IDs accepted by a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VlanTable {
+    bits: [u64; 64],
+}
+
+impl Default for VlanTable {
+    fn default() -> Self {
+        Self { bits: [0; 64] }
+    }
+}
+
+impl VlanTable {
+    pub fn contains(&self, vid: u16) -> bool {
+        vid < 4096 && self.bits[(vid / 64) as usize] & (1 << (vid % 64)) != 0
+    }
+
+    fn set(&mut self, vid: u16, member: bool) {
+        let word = &mut self.bits[(vid / 64) as usize];
+        if member {
+            *word |= 1 << (vid % 64);
+        } else {
+            *word &= !(1 << (vid % 64));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// VLAN IDs in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (1..=MAX_VLAN_ID).filter(|vid| self.contains(*vid))
+    }
+}
+
+/// Receive mode of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RxMode {
+    pub promiscuous: bool,
+    pub all_multicast: bool,
+    pub mac: [u8; 6],
+    pub vlans: VlanTable,
+    /// IPv4 addresses announced with gratuitous ARP
+    pub announce: Vec<u32>,
+}
+
+impl RxMode {
+    pub fn new(mac: [u8; 6]) -> Self {
+        Self { promiscuous: false, all_multicast: false, mac, vlans: VlanTable::default(), announce: Vec::new() }
+    }
+
+    pub fn flags(&self) -> u32 {
+        let mut flags = 0;
+        if self.promiscuous {
+            flags |= RX_FLAG_PROMISCUOUS;
+        }
+        if self.all_multicast {
+            flags |= RX_FLAG_ALL_MULTICAST;
+        }
+        flags
+    }
+
+    /// Frames telling the network where this station now is: a
+    /// gratuitous ARP per announced address or, knowing none, a RARP
+    /// request as hypervisors send on behalf of their guests
+    pub fn announce_frames(&self) -> Vec<[u8; ANNOUNCE_FRAME_SIZE]> {
+        if self.announce.is_empty() {
+            return alloc::vec![announce_frame(ETHERTYPE_RARP, 3, &self.mac, 0)];
+        }
+        self.announce.iter().map(|address| announce_frame(ETHERTYPE_ARP, 1, &self.mac, *address)).collect()
+    }
+}
+
+/// Broadcast ARP or RARP frame with the station as sender and target
+fn announce_frame(ethertype: u16, operation: u16, mac: &[u8; 6], address: u32) -> [u8; ANNOUNCE_FRAME_SIZE] {
+    let mut frame = [0u8; ANNOUNCE_FRAME_SIZE];
+    frame[0..6].copy_from_slice(&[0xFF; 6]);
+    frame[6..12].copy_from_slice(mac);
+    frame[12..14].copy_from_slice(&ethertype.to_be_bytes());
+    // Ethernet hardware, IPv4 protocol, 6 and 4 byte addresses
+    frame[14..22].copy_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4, 0, 0]);
+    frame[20..22].copy_from_slice(&operation.to_be_bytes());
+    frame[22..28].copy_from_slice(mac);
+    frame[28..32].copy_from_slice(&address.to_be_bytes());
+    frame[32..38].copy_from_slice(mac);
+    frame[38..42].copy_from_slice(&address.to_be_bytes());
+    frame
+}
+
+fn read_u16(bytes: &[u8]) -> Option<u16> {
+    (bytes.len() == 2).then(|| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &[u8]) -> Option<u32> {
+    bytes.get(0..4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn vlan_id(args: &[u8]) -> Option<u16> {
+    read_u16(args).filter(|vid| (1..=MAX_VLAN_ID).contains(vid))
+}
+
+fn rx_mode<F>(mode: &mut RxMode, request: &[u8], apply: &mut F, out: &mut Vec<u8>) -> i32
+where
+    F: FnMut(&RxModeChange) -> Result<(), i32>,
+{
+    let Some(opcode) = read_u32(request) else {
+        return CTRL_EINVAL;
+    };
+    let args = &request[4..];
+    match opcode {
+        RX_MODE_GET => {
+            out.extend_from_slice(&mode.flags().to_le_bytes());
+            out.extend_from_slice(&mode.mac);
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(&(mode.vlans.len() as u32).to_le_bytes());
+            for vid in mode.vlans.iter() {
+                out.extend_from_slice(&vid.to_le_bytes());
+            }
+            CTRL_OK
+        }
+        RX_MODE_SET_FLAGS => {
+            let Some(flags) = read_u32(args).filter(|flags| args.len() == 4 && flags & !3 == 0) else {
+                return CTRL_EINVAL;
+            };
+            let promiscuous = flags & RX_FLAG_PROMISCUOUS != 0;
+            let all_multicast = flags & RX_FLAG_ALL_MULTICAST != 0;
+            if let Err(status) = apply(&RxModeChange::Flags { promiscuous, all_multicast }) {
+                return status;
+            }
+            mode.promiscuous = promiscuous;
+            mode.all_multicast = all_multicast;
+            CTRL_OK
+        }
+        RX_MODE_SET_MAC => {
+            let mac: [u8; 6] = match args.try_into() {
+                Ok(mac) => mac,
+                Err(_) => return CTRL_EINVAL,
+            };
+            // Stations need a unicast address
+            if mac[0] & 1 != 0 || mac == [0; 6] {
+                return CTRL_EINVAL;
+            }
+            if let Err(status) = apply(&RxModeChange::Mac(mac)) {
+                return status;
+            }
+            mode.mac = mac;
+            CTRL_OK
+        }
+        RX_MODE_ADD_VLAN => {
+            let Some(vid) = vlan_id(args) else {
+                return CTRL_EINVAL;
+            };
+            if !mode.vlans.contains(vid) {
+                if let Err(status) = apply(&RxModeChange::AddVlan(vid)) {
+                    return status;
+                }
+                mode.vlans.set(vid, true);
+            }
+            CTRL_OK
+        }
+        RX_MODE_REMOVE_VLAN => {
+            let Some(vid) = vlan_id(args) else {
+                return CTRL_EINVAL;
+            };
+            if !mode.vlans.contains(vid) {
+                return CTRL_ENOENT;
+            }
+            if let Err(status) = apply(&RxModeChange::RemoveVlan(vid)) {
+                return status;
+            }
+            mode.vlans.set(vid, false);
+            CTRL_OK
+        }
+        RX_MODE_SET_ANNOUNCE => {
+            let count = match read_u32(args) {
+                Some(count) if count as usize <= MAX_ANNOUNCE_ADDRESSES && args.len() == 4 + count as usize * 4 => {
+                    count as usize
+                }
+                _ => return CTRL_EINVAL,
+            };
+            mode.announce = args[4..].chunks_exact(4).take(count).filter_map(read_u32).collect();
+            CTRL_OK
+        }
+        _ => CTRL_EINVAL,
+    }
+}
+
+/// Serve a receive mode request and build the reply: i32 status then,
+/// for GET, the current mode. `apply` makes a change on the hardware
+/// and returns the status to report when it cannot.
+pub fn handle_rx_mode<F>(mode: &mut RxMode, request: &[u8], mut apply: F) -> Vec<u8>
+where
+    F: FnMut(&RxModeChange) -> Result<(), i32>,
+{
+    let mut payload = Vec::new();
+    let status = rx_mode(mode, request, &mut apply, &mut payload);
+
+    let mut reply = Vec::with_capacity(4 + payload.len());
+    reply.extend_from_slice(&status.to_le_bytes());
+    if status == CTRL_OK {
+        reply.extend_from_slice(&payload);
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+    const EIO: i32 = -5;
+
+    fn request(opcode: u32, payload: &[u8]) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        request.extend_from_slice(payload);
+        request
+    }
+
+    fn status(reply: &[u8]) -> i32 {
+        i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]])
+    }
+
+    #[test]
+    fn test_applies_changes_before_recording_them() {
+        let mut mode = RxMode::new(MAC);
+        let mut applied = Vec::new();
+        let mut accept = |change: &RxModeChange| {
+            applied.push(*change);
+            Ok(())
+        };
+
+        let flags = (RX_FLAG_PROMISCUOUS | RX_FLAG_ALL_MULTICAST).to_le_bytes();
+        assert_eq!(status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_FLAGS, &flags), &mut accept)), CTRL_OK);
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_ADD_VLAN, &100u16.to_le_bytes()), &mut accept)),
+            CTRL_OK
+        );
+        // Already in the table: nothing to apply
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_ADD_VLAN, &100u16.to_le_bytes()), &mut accept)),
+            CTRL_OK
+        );
+        assert_eq!(
+            applied,
+            [RxModeChange::Flags { promiscuous: true, all_multicast: true }, RxModeChange::AddVlan(100)]
+        );
+
+        let refuse = |_: &RxModeChange| Err(EIO);
+        let mac = [0x02, 0, 0, 0, 0, 1];
+        assert_eq!(status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_MAC, &mac), refuse)), EIO);
+        assert_eq!(mode.mac, MAC);
+        assert_eq!(status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_MAC, &mac), |_| Ok(()))), CTRL_OK);
+        assert_eq!(mode.mac, mac);
+
+        let reply = handle_rx_mode(&mut mode, &request(RX_MODE_GET, &[]), |_| Ok(()));
+        assert_eq!(status(&reply), CTRL_OK);
+        assert_eq!(reply[4..8], 3u32.to_le_bytes());
+        assert_eq!(reply[8..14], mac);
+        assert_eq!(reply[16..20], 1u32.to_le_bytes());
+        assert_eq!(reply[20..22], 100u16.to_le_bytes());
+
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_REMOVE_VLAN, &100u16.to_le_bytes()), |_| Ok(()))),
+            CTRL_OK
+        );
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_REMOVE_VLAN, &100u16.to_le_bytes()), |_| Ok(()))),
+            CTRL_ENOENT
+        );
+        assert!(mode.vlans.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_bad_requests() {
+        let mut mode = RxMode::new(MAC);
+        let ok = |_: &RxModeChange| Ok(());
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_FLAGS, &4u32.to_le_bytes()), ok)),
+            CTRL_EINVAL
+        );
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_MAC, &[0x01, 0, 0x5E, 0, 0, 1]), ok)),
+            CTRL_EINVAL
+        );
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_ADD_VLAN, &4095u16.to_le_bytes()), ok)),
+            CTRL_EINVAL
+        );
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_ADD_VLAN, &0u16.to_le_bytes()), ok)),
+            CTRL_EINVAL
+        );
+        assert_eq!(
+            status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_ANNOUNCE, &2u32.to_le_bytes()), ok)),
+            CTRL_EINVAL
+        );
+        assert_eq!(status(&handle_rx_mode(&mut mode, &[2, 0], ok)), CTRL_EINVAL);
+    }
+
+    #[test]
+    fn test_builds_announce_frames() {
+        let mut mode = RxMode::new(MAC);
+        let rarp = mode.announce_frames();
+        assert_eq!(rarp.len(), 1);
+        assert_eq!(rarp[0][12..14], [0x80, 0x35]);
+        assert_eq!(rarp[0][20..22], [0, 3]);
+
+        let mut addresses = 2u32.to_le_bytes().to_vec();
+        addresses.extend_from_slice(&0x0A00_0002u32.to_le_bytes());
+        addresses.extend_from_slice(&0xC0A8_0105u32.to_le_bytes());
+        assert_eq!(status(&handle_rx_mode(&mut mode, &request(RX_MODE_SET_ANNOUNCE, &addresses), |_| Ok(()))), CTRL_OK);
+        let frames = mode.announce_frames();
+        assert_eq!(frames.len(), 2);
+        let garp = &frames[1];
+        assert_eq!(garp[0..6], [0xFF; 6]);
+        assert_eq!(garp[6..12], MAC);
+        assert_eq!(garp[12..14], [0x08, 0x06]);
+        assert_eq!(garp[14..22], [0, 1, 8, 0, 6, 4, 0, 1]);
+        // Sender and target are both the announced address
+        assert_eq!(garp[28..32], [192, 168, 1, 5]);
+        assert_eq!(garp[38..42], [192, 168, 1, 5]);
+    }
+}
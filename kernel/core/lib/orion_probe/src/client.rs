@@ -0,0 +1,170 @@
+/*
+ * Orion Operating System - Probe Client
+ *
+ * Client side of the probe control requests, used by orion-trace. The
+ * transport is a trait so the tool can call through the driver's IPC
+ * channel and tests straight into handle_control.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::control::{
+    decode_record, decode_status, read_u32, read_u64, PROBE_CTRL_DISABLE, PROBE_CTRL_ENABLE, PROBE_CTRL_QUERY,
+    PROBE_CTRL_READ, QUERY_SIZE, RECORD_SIZE, STATUS_OK,
+};
+use crate::probes::{ProbeRecord, ProbeStatus};
+
+/// Status a driver answered with, or STATUS_EIO when it did not answer
+/// properly
+pub const STATUS_EIO: i32 = -5;
+
+/// Carries a control request to a driver and returns its reply
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Records returned by one read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceBatch {
+    pub records: Vec<ProbeRecord>,
+    /// Records overwritten before they could be read
+    pub lost: u64,
+}
+
+pub struct ProbeClient<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> ProbeClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Send a request and return the reply payload of a successful call
+    fn call(&mut self, opcode: u32, argument: &[u8]) -> Result<Vec<u8>, i32> {
+        let mut request = Vec::with_capacity(4 + argument.len());
+        request.extend_from_slice(&opcode.to_le_bytes());
+        request.extend_from_slice(argument);
+        let response = self.transport.call(&request).ok_or(STATUS_EIO)?;
+        match read_u32(&response, 0).ok_or(STATUS_EIO)? as i32 {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    pub fn query(&mut self) -> Result<ProbeStatus, i32> {
+        let payload = self.call(PROBE_CTRL_QUERY, &[])?;
+        if payload.len() < QUERY_SIZE {
+            return Err(STATUS_EIO);
+        }
+        decode_status(&payload).ok_or(STATUS_EIO)
+    }
+
+    pub fn enable(&mut self, mask: u32, rate: u32) -> Result<(), i32> {
+        let mut argument = mask.to_le_bytes().to_vec();
+        argument.extend_from_slice(&rate.to_le_bytes());
+        self.call(PROBE_CTRL_ENABLE, &argument).map(|_| ())
+    }
+
+    pub fn disable(&mut self, mask: u32) -> Result<(), i32> {
+        self.call(PROBE_CTRL_DISABLE, &mask.to_le_bytes()).map(|_| ())
+    }
+
+    /// Up to `max` records from sequence number `from` on
+    pub fn read(&mut self, from: u64, max: u32) -> Result<TraceBatch, i32> {
+        let mut argument = from.to_le_bytes().to_vec();
+        argument.extend_from_slice(&max.to_le_bytes());
+        let payload = self.call(PROBE_CTRL_READ, &argument)?;
+        let lost = read_u64(&payload, 0).ok_or(STATUS_EIO)?;
+        let count = read_u32(&payload, 8).ok_or(STATUS_EIO)? as usize;
+        let records = payload
+            .get(12..)
+            .ok_or(STATUS_EIO)?
+            .chunks_exact(RECORD_SIZE)
+            .take(count)
+            .map(decode_record)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(STATUS_EIO)?;
+        if records.len() != count {
+            return Err(STATUS_EIO);
+        }
+        Ok(TraceBatch { records, lost })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::handle_control;
+    use crate::points::{BLOCK_READ_BLOCKS, DRIVER_HANDLE_IRQ};
+    use crate::probes::{Probes, RecordKind};
+
+    fn clock() -> u64 {
+        42
+    }
+
+    static PROBES: Probes = Probes::new(clock);
+
+    struct Direct;
+
+    impl Transport for Direct {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            Some(handle_control(&PROBES, request))
+        }
+    }
+
+    #[derive(Debug)]
+    enum DriverError {
+        Timeout,
+    }
+
+    struct Disk {
+        reads: u32,
+    }
+
+    impl Disk {
+        fn read_blocks(&mut self, lba: u64, count: u32, buffer: &mut [u8]) -> Result<usize, DriverError> {
+            crate::probe!(PROBES, BLOCK_READ_BLOCKS, [lba, count, buffer], {
+                if lba > 1000 {
+                    return Err(DriverError::Timeout);
+                }
+                self.reads += 1;
+                Ok(buffer.len())
+            })
+        }
+    }
+
+    #[test]
+    fn test_drives_probes_through_control_requests() {
+        let mut client = ProbeClient::new(Direct);
+        let mut disk = Disk { reads: 0 };
+        let mut buffer = [0u8; 512];
+        disk.read_blocks(0, 1, &mut buffer).unwrap();
+        assert_eq!(client.query().unwrap().next_sequence, 1);
+
+        client.enable(BLOCK_READ_BLOCKS.bit() | DRIVER_HANDLE_IRQ.bit(), 0).unwrap();
+        assert_eq!(client.enable(1 << 31, 0), Err(crate::control::STATUS_EINVAL));
+        disk.read_blocks(8, 1, &mut buffer).unwrap();
+        assert!(disk.read_blocks(4096, 1, &mut buffer).is_err());
+        client.disable(BLOCK_READ_BLOCKS.bit()).unwrap();
+        disk.read_blocks(16, 1, &mut buffer).unwrap();
+        assert_eq!(disk.reads, 3);
+
+        let status = client.query().unwrap();
+        assert_eq!((status.enabled, status.next_sequence), (DRIVER_HANDLE_IRQ.bit(), 5));
+        let batch = client.read(1, 16).unwrap();
+        assert_eq!(batch.lost, 0);
+        let kinds: Vec<_> = batch.records.iter().map(|record| record.kind).collect();
+        assert_eq!(kinds, [RecordKind::Entry, RecordKind::Exit, RecordKind::Entry, RecordKind::Error]);
+        assert_eq!(&batch.records[0].args[..3], &[8, 1, 512]);
+        assert_eq!(batch.records[1].args[0], 512);
+        assert_eq!(batch.records[3].error.as_str(), "Timeout");
+        assert_eq!(batch.records[3].args[1], 0);
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Orion Operating System - Probe Control
+ *
+ * Requests drivers accept through an ioctl of length PROBE_IOCTL_CONTROL
+ * to switch their probes and hand out the trace ring. All fields are
+ * little-endian; every request starts with a 32-bit opcode and every
+ * reply with a 32-bit signed status (0 or a negative errno).
+ *
+ *   QUERY                      -> enabled:u32 rate:u32 limited:u64
+ *                                 dropped:u64 next_sequence:u64
+ *   ENABLE   mask:u32 rate:u32 -> (empty)
+ *   DISABLE  mask:u32          -> (empty)
+ *   READ     from:u64 max:u32  -> lost:u64 count:u32 {record}*
+ *
+ * A mask has the bit of every probe point it covers (see points). ENABLE
+ * adds to the points enabled and sets the limit of entry records per
+ * second for all of them, 0 for none. A record is
+ *
+ *   sequence:u64 time_ns:u64 point:u8 kind:u8 argc:u8 pad:u8
+ *   args:[u64; 4] error[16]
+ *
+ * The opcodes do not overlap those of the firmware or raw disk control
+ * requests, so a driver can serve them all on one channel.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::points::{ProbePoint, POINTS};
+use crate::probes::{ProbeRecord, ProbeStatus, Probes, RecordKind, MAX_ARGS};
+use crate::value::{ErrorName, ERROR_NAME_SIZE};
+
+/// Ioctl length selecting probe control requests
+pub const PROBE_IOCTL_CONTROL: u64 = 0x3030;
+
+// Opcodes
+pub const PROBE_CTRL_QUERY: u32 = 0x4001;
+pub const PROBE_CTRL_ENABLE: u32 = 0x4002;
+pub const PROBE_CTRL_DISABLE: u32 = 0x4003;
+pub const PROBE_CTRL_READ: u32 = 0x4004;
+
+/// Most records one READ returns
+pub const MAX_READ_RECORDS: u32 = 64;
+
+pub const QUERY_SIZE: usize = 32;
+pub const RECORD_SIZE: usize = 20 + 8 * MAX_ARGS + ERROR_NAME_SIZE;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Mask of every known probe point
+fn all_points() -> u32 {
+    POINTS.iter().fold(0, |mask, info| mask | info.point.bit())
+}
+
+pub fn encode_status(status: &ProbeStatus, out: &mut Vec<u8>) {
+    out.extend_from_slice(&status.enabled.to_le_bytes());
+    out.extend_from_slice(&status.rate.to_le_bytes());
+    out.extend_from_slice(&status.limited.to_le_bytes());
+    out.extend_from_slice(&status.dropped.to_le_bytes());
+    out.extend_from_slice(&status.next_sequence.to_le_bytes());
+}
+
+pub fn decode_status(data: &[u8]) -> Option<ProbeStatus> {
+    Some(ProbeStatus {
+        enabled: read_u32(data, 0)?,
+        rate: read_u32(data, 4)?,
+        limited: read_u64(data, 8)?,
+        dropped: read_u64(data, 16)?,
+        next_sequence: read_u64(data, 24)?,
+    })
+}
+
+pub fn encode_record(record: &ProbeRecord, out: &mut Vec<u8>) {
+    out.extend_from_slice(&record.sequence.to_le_bytes());
+    out.extend_from_slice(&record.time_ns.to_le_bytes());
+    out.extend_from_slice(&[record.point.0, record.kind as u8, record.argc, 0]);
+    for arg in record.args {
+        out.extend_from_slice(&arg.to_le_bytes());
+    }
+    out.extend_from_slice(&record.error.0);
+}
+
+pub fn decode_record(data: &[u8]) -> Option<ProbeRecord> {
+    let mut args = [0u64; MAX_ARGS];
+    for (index, arg) in args.iter_mut().enumerate() {
+        *arg = read_u64(data, 20 + 8 * index)?;
+    }
+    let mut error = [0u8; ERROR_NAME_SIZE];
+    error.copy_from_slice(data.get(20 + 8 * MAX_ARGS..RECORD_SIZE)?);
+    Some(ProbeRecord {
+        sequence: read_u64(data, 0)?,
+        time_ns: read_u64(data, 8)?,
+        point: ProbePoint(data[16]),
+        kind: RecordKind::from_u8(data[17])?,
+        argc: data[18].min(MAX_ARGS as u8),
+        args,
+        error: ErrorName(error),
+    })
+}
+
+fn control(probes: &Probes, request: &[u8], out: &mut Vec<u8>) -> i32 {
+    let Some(opcode) = read_u32(request, 0) else {
+        return STATUS_EINVAL;
+    };
+    match opcode {
+        PROBE_CTRL_QUERY => {
+            encode_status(&probes.status(), out);
+            STATUS_OK
+        }
+        PROBE_CTRL_ENABLE => match (read_u32(request, 4), read_u32(request, 8)) {
+            (Some(mask), Some(rate)) if mask & !all_points() == 0 => {
+                probes.enable(mask, rate);
+                STATUS_OK
+            }
+            _ => STATUS_EINVAL,
+        },
+        PROBE_CTRL_DISABLE => match read_u32(request, 4) {
+            Some(mask) => {
+                probes.disable(mask);
+                STATUS_OK
+            }
+            None => STATUS_EINVAL,
+        },
+        PROBE_CTRL_READ => match (read_u64(request, 4), read_u32(request, 12)) {
+            (Some(from), Some(max)) => match probes.read(from, max.min(MAX_READ_RECORDS) as usize) {
+                Some((records, lost)) => {
+                    out.extend_from_slice(&lost.to_le_bytes());
+                    out.extend_from_slice(&(records.len() as u32).to_le_bytes());
+                    for record in &records {
+                        encode_record(record, out);
+                    }
+                    STATUS_OK
+                }
+                None => STATUS_EBUSY,
+            },
+            _ => STATUS_EINVAL,
+        },
+        _ => STATUS_EINVAL,
+    }
+}
+
+/// Serve a probe control request and build the reply: i32 status followed
+/// by the payload of the operation
+pub fn handle_control(probes: &Probes, request: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let status = control(probes, request, &mut payload);
+    let mut reply = Vec::with_capacity(4 + payload.len());
+    reply.extend_from_slice(&status.to_le_bytes());
+    if status == STATUS_OK {
+        reply.extend_from_slice(&payload);
+    }
+    reply
+}
@@ -0,0 +1,73 @@
+/*
+ * Orion Operating System - Driver Probes
+ *
+ * Dynamic instrumentation of the driver traits. A driver wraps the body
+ * of an OrionDriver, BlockDriver or NetworkDriver method in probe!,
+ * naming the probe point and the arguments to capture; while the point is
+ * disabled the wrapper costs one atomic load and no argument is
+ * evaluated. Once orion-trace enables it through the control requests,
+ * every call leaves an entry record with its arguments and an exit record
+ * with its return value or error and its duration in the driver's trace
+ * ring, within the rate limit set with it.
+ *
+ * Drivers keep a Probes in a static, feed control requests to
+ * handle_control and mark probed trait methods with probe!; orion-trace
+ * drives them through ProbeClient.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod client;
+pub mod control;
+pub mod points;
+pub mod probes;
+pub mod value;
+
+pub use client::{ProbeClient, TraceBatch, Transport};
+pub use control::{handle_control, PROBE_IOCTL_CONTROL};
+pub use points::{mask_of, PointInfo, ProbePoint, POINTS};
+pub use probes::{ProbeRecord, ProbeStatus, Probes, RecordKind, MAX_ARGS, RING_SIZE};
+pub use value::{ErrorName, Outcome, ProbeArg, ProbeReturn};
+
+/// Trace a call to a probed method.
+///
+/// `probe!(PROBES, POINT, [args], { body })` evaluates to `body`, recording
+/// the entry with the values of `args` (at most MAX_ARGS of them are kept)
+/// and the return once the body is done. `return` and `?` in the body
+/// leave the body only, so early returns are recorded as well. In an async
+/// method write `async { body }` so the body can await.
+#[macro_export]
+macro_rules! probe {
+    (@enter $probes:expr, $point:expr, [$($arg:expr),*]) => {
+        if $probes.armed($point) {
+            $probes.enter($point, &[$($crate::ProbeArg::probe_value(&$arg)),*])
+        } else {
+            None
+        }
+    };
+    ($probes:expr, $point:expr, [$($arg:expr),* $(,)?], async $body:block) => {{
+        let entered = $crate::probe!(@enter $probes, $point, [$($arg),*]);
+        let result = async $body.await;
+        if let Some(entered) = entered {
+            $probes.exit($point, entered, &result);
+        }
+        result
+    }};
+    ($probes:expr, $point:expr, [$($arg:expr),* $(,)?], $body:block) => {{
+        let entered = $crate::probe!(@enter $probes, $point, [$($arg),*]);
+        #[allow(clippy::redundant_closure_call)]
+        let result = (|| $body)();
+        if let Some(entered) = entered {
+            $probes.exit($point, entered, &result);
+        }
+        result
+    }};
+}
@@ -0,0 +1,97 @@
+/*
+ * Orion Operating System - Probe Points
+ *
+ * The driver trait methods a probe can be placed on. Each point has a
+ * fixed index, used as its bit in the enable mask and carried in trace
+ * records, so drivers, the control requests and orion-trace agree on
+ * them without exchanging names.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+/// One probed trait method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProbePoint(pub u8);
+
+/// Trait and method of a probe point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointInfo {
+    pub point: ProbePoint,
+    pub trait_name: &'static str,
+    pub method: &'static str,
+}
+
+macro_rules! probe_points {
+    ($($name:ident = $index:literal => $trait_name:literal :: $method:literal;)*) => {
+        $(pub const $name: ProbePoint = ProbePoint($index);)*
+
+        /// Every probe point, by index
+        pub const POINTS: &[PointInfo] = &[
+            $(PointInfo { point: $name, trait_name: $trait_name, method: $method },)*
+        ];
+    };
+}
+
+probe_points! {
+    DRIVER_PROBE = 0 => "OrionDriver"::"probe";
+    DRIVER_INIT = 1 => "OrionDriver"::"init";
+    DRIVER_HANDLE_IRQ = 2 => "OrionDriver"::"handle_irq";
+    DRIVER_SHUTDOWN = 3 => "OrionDriver"::"shutdown";
+    DRIVER_HANDLE_MESSAGE = 4 => "OrionDriver"::"handle_message";
+    BLOCK_READ_BLOCKS = 5 => "BlockDriver"::"read_blocks";
+    BLOCK_WRITE_BLOCKS = 6 => "BlockDriver"::"write_blocks";
+    BLOCK_FLUSH = 7 => "BlockDriver"::"flush";
+    BLOCK_GET_CAPACITY = 8 => "BlockDriver"::"get_capacity";
+    BLOCK_GET_BLOCK_SIZE = 9 => "BlockDriver"::"get_block_size";
+    NET_SEND_PACKET = 10 => "NetworkDriver"::"send_packet";
+    NET_RECEIVE_PACKET = 11 => "NetworkDriver"::"receive_packet";
+    NET_SET_PROMISCUOUS = 12 => "NetworkDriver"::"set_promiscuous";
+    NET_GET_MAC_ADDRESS = 13 => "NetworkDriver"::"get_mac_address";
+    NET_SET_MAC_ADDRESS = 14 => "NetworkDriver"::"set_mac_address";
+}
+
+impl ProbePoint {
+    /// Bit of the point in an enable mask
+    pub const fn bit(self) -> u32 {
+        1 << self.0
+    }
+
+    pub fn info(self) -> Option<&'static PointInfo> {
+        POINTS.get(self.0 as usize)
+    }
+}
+
+/// Mask of the points named by `pattern`: "all", a trait ("BlockDriver"),
+/// a method ("read_blocks") or both ("BlockDriver::read_blocks")
+pub fn mask_of(pattern: &str) -> u32 {
+    let matches = |info: &PointInfo| match pattern.split_once("::") {
+        _ if pattern == "all" => true,
+        Some((trait_name, method)) => info.trait_name == trait_name && info.method == method,
+        None => info.trait_name == pattern || info.method == pattern,
+    };
+    POINTS.iter().filter(|info| matches(info)).fold(0, |mask, info| mask | info.point.bit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_points_are_indexed_in_order() {
+        assert!(POINTS.len() <= 32);
+        for (index, info) in POINTS.iter().enumerate() {
+            assert_eq!(info.point.0 as usize, index);
+        }
+        assert_eq!(BLOCK_READ_BLOCKS.info().unwrap().method, "read_blocks");
+
+        assert_eq!(mask_of("BlockDriver::write_blocks"), BLOCK_WRITE_BLOCKS.bit());
+        assert_eq!(mask_of("handle_irq"), DRIVER_HANDLE_IRQ.bit());
+        assert_eq!(mask_of("NetworkDriver").count_ones(), 5);
+        assert_eq!(mask_of("all").count_ones() as usize, POINTS.len());
+        assert_eq!(mask_of("NetworkDriver::read_blocks"), 0);
+    }
+}
@@ -0,0 +1,327 @@
+/*
+ * Orion Operating System - Driver Probes
+ *
+ * The probe state of one driver: which points are enabled, the rate
+ * limit and the trace ring the records go to. A driver keeps one Probes
+ * in a static and wraps its trait methods in probe!; a disabled point
+ * costs one atomic load. Enabling and disabling happen at runtime through
+ * the control requests, without rebuilding the driver.
+ *
+ * The rate limit is a token bucket on entry records. An exit is recorded
+ * exactly when its entry was, so every call in the ring has both. The
+ * ring keeps the last RING_SIZE records; readers follow it by sequence
+ * number and learn how many they missed.
+ *
+ * A probe never waits: when the ring is held by a reader, or by a probe
+ * that interrupted it, the record is dropped and counted.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use crate::points::ProbePoint;
+use crate::value::{ErrorName, Outcome, ProbeReturn, ERROR_NAME_SIZE};
+
+/// Arguments kept per entry record
+pub const MAX_ARGS: usize = 4;
+
+/// Records kept in the ring
+pub const RING_SIZE: usize = 256;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// args holds the first `argc` arguments
+    Entry = 1,
+    /// args[0] is the return value, args[1] the time spent in the call
+    Exit = 2,
+    /// error names the error returned, args[1] is the time spent in the call
+    Error = 3,
+}
+
+impl RecordKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(RecordKind::Entry),
+            2 => Some(RecordKind::Exit),
+            3 => Some(RecordKind::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeRecord {
+    pub sequence: u64,
+    pub time_ns: u64,
+    pub point: ProbePoint,
+    pub kind: RecordKind,
+    pub argc: u8,
+    pub args: [u64; MAX_ARGS],
+    pub error: ErrorName,
+}
+
+impl ProbeRecord {
+    const EMPTY: ProbeRecord = ProbeRecord {
+        sequence: 0,
+        time_ns: 0,
+        point: ProbePoint(0),
+        kind: RecordKind::Entry,
+        argc: 0,
+        args: [0; MAX_ARGS],
+        error: ErrorName([0; ERROR_NAME_SIZE]),
+    };
+}
+
+/// Settings and counters of a driver's probes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProbeStatus {
+    pub enabled: u32,
+    /// Entry records per second, 0 for no limit
+    pub rate: u32,
+    /// Calls the rate limit left out
+    pub limited: u64,
+    /// Records lost because the ring was busy
+    pub dropped: u64,
+    /// Sequence number the next record gets
+    pub next_sequence: u64,
+}
+
+struct Ring {
+    records: [ProbeRecord; RING_SIZE],
+    next_sequence: u64,
+    tokens: u64,
+    /// Time the bucket was last refilled up to
+    refilled_ns: u64,
+}
+
+impl Ring {
+    /// Take an entry token; rate is nonzero
+    fn take(&mut self, rate: u64, now_ns: u64) -> bool {
+        let elapsed = now_ns.saturating_sub(self.refilled_ns);
+        let refill = ((elapsed as u128 * rate as u128) / NANOS_PER_SEC as u128) as u64;
+        if refill > 0 {
+            self.tokens = (self.tokens + refill).min(rate);
+            // Keep the remainder so slow rates still refill
+            self.refilled_ns += ((refill as u128 * NANOS_PER_SEC as u128) / rate as u128) as u64;
+        }
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+
+    fn push(&mut self, mut record: ProbeRecord) {
+        record.sequence = self.next_sequence;
+        self.records[(self.next_sequence % RING_SIZE as u64) as usize] = record;
+        self.next_sequence += 1;
+    }
+}
+
+pub struct Probes {
+    clock: fn() -> u64,
+    enabled: AtomicU32,
+    rate: AtomicU32,
+    limited: AtomicU64,
+    dropped: AtomicU64,
+    busy: AtomicBool,
+    ring: UnsafeCell<Ring>,
+}
+
+// The ring is only reached with `busy` held
+unsafe impl Sync for Probes {}
+
+impl Probes {
+    /// Probes timestamped with `clock`, a monotonic clock in nanoseconds,
+    /// all disabled
+    pub const fn new(clock: fn() -> u64) -> Self {
+        Self {
+            clock,
+            enabled: AtomicU32::new(0),
+            rate: AtomicU32::new(0),
+            limited: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            busy: AtomicBool::new(false),
+            ring: UnsafeCell::new(Ring {
+                records: [ProbeRecord::EMPTY; RING_SIZE],
+                next_sequence: 1,
+                tokens: 0,
+                refilled_ns: 0,
+            }),
+        }
+    }
+
+    /// Run `f` on the ring unless someone holds it
+    fn with_ring<R>(&self, f: impl FnOnce(&mut Ring) -> R) -> Option<R> {
+        if self.busy.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return None;
+        }
+        let result = f(unsafe { &mut *self.ring.get() });
+        self.busy.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    pub fn armed(&self, point: ProbePoint) -> bool {
+        self.enabled.load(Ordering::Relaxed) & point.bit() != 0
+    }
+
+    /// Enable the points of `mask` on top of those already enabled and set
+    /// the rate limit; the bucket starts full
+    pub fn enable(&self, mask: u32, rate: u32) {
+        let now_ns = (self.clock)();
+        self.with_ring(|ring| {
+            ring.tokens = rate as u64;
+            ring.refilled_ns = now_ns;
+        });
+        self.rate.store(rate, Ordering::Relaxed);
+        self.enabled.fetch_or(mask, Ordering::Relaxed);
+    }
+
+    pub fn disable(&self, mask: u32) {
+        self.enabled.fetch_and(!mask, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> ProbeStatus {
+        ProbeStatus {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            rate: self.rate.load(Ordering::Relaxed),
+            limited: self.limited.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            next_sequence: self.with_ring(|ring| ring.next_sequence).unwrap_or(0),
+        }
+    }
+
+    /// Record the entry of a call to `point` with its arguments. Returns
+    /// the time of the entry when the call is traced, to be handed to
+    /// exit
+    pub fn enter(&self, point: ProbePoint, args: &[u64]) -> Option<u64> {
+        let now_ns = (self.clock)();
+        let rate = self.rate.load(Ordering::Relaxed) as u64;
+        let mut record = ProbeRecord { time_ns: now_ns, point, ..ProbeRecord::EMPTY };
+        record.argc = args.len().min(MAX_ARGS) as u8;
+        record.args[..record.argc as usize].copy_from_slice(&args[..record.argc as usize]);
+        let traced = self.with_ring(|ring| {
+            if rate != 0 && !ring.take(rate, now_ns) {
+                return false;
+            }
+            ring.push(record);
+            true
+        });
+        match traced {
+            Some(true) => Some(now_ns),
+            Some(false) => {
+                self.limited.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Record the return of a call whose entry was recorded at `entered_ns`
+    pub fn exit(&self, point: ProbePoint, entered_ns: u64, result: &dyn ProbeReturn) {
+        let now_ns = (self.clock)();
+        let mut record = ProbeRecord { time_ns: now_ns, point, kind: RecordKind::Exit, ..ProbeRecord::EMPTY };
+        record.args[1] = now_ns.saturating_sub(entered_ns);
+        match result.probe_return() {
+            Outcome::Value(value) => record.args[0] = value,
+            Outcome::Error(name) => {
+                record.kind = RecordKind::Error;
+                record.error = name;
+            }
+        }
+        if self.with_ring(|ring| ring.push(record)).is_none() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Up to `max` records from sequence number `from` on, with the
+    /// number of records from `from` already overwritten
+    pub fn read(&self, from: u64, max: usize) -> Option<(Vec<ProbeRecord>, u64)> {
+        self.with_ring(|ring| {
+            let oldest = ring.next_sequence.saturating_sub(RING_SIZE as u64).max(1);
+            let start = from.max(oldest);
+            let lost = start - from.min(start);
+            let records = (start..ring.next_sequence)
+                .take(max)
+                .map(|sequence| ring.records[(sequence % RING_SIZE as u64) as usize])
+                .collect();
+            (records, lost)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::points::{BLOCK_READ_BLOCKS, BLOCK_WRITE_BLOCKS};
+    use core::sync::atomic::AtomicU64;
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+
+    fn clock() -> u64 {
+        NOW.load(Ordering::Relaxed)
+    }
+
+    #[derive(Debug)]
+    enum DriverError {
+        IoError,
+    }
+
+    #[test]
+    fn test_records_enabled_calls_within_the_rate() {
+        let probes = Probes::new(clock);
+        assert!(!probes.armed(BLOCK_WRITE_BLOCKS));
+
+        probes.enable(BLOCK_WRITE_BLOCKS.bit(), 2);
+        assert!(probes.armed(BLOCK_WRITE_BLOCKS));
+        assert!(!probes.armed(BLOCK_READ_BLOCKS));
+        NOW.store(1_000, Ordering::Relaxed);
+        let entered = probes.enter(BLOCK_WRITE_BLOCKS, &[16, 2, 1024, 0, 99]).unwrap();
+        NOW.store(6_000, Ordering::Relaxed);
+        probes.exit(BLOCK_WRITE_BLOCKS, entered, &Ok::<usize, DriverError>(1024));
+        let entered = probes.enter(BLOCK_WRITE_BLOCKS, &[]).unwrap();
+        probes.exit(BLOCK_WRITE_BLOCKS, entered, &Err::<usize, _>(DriverError::IoError));
+        // Bucket empty until a second has gone by
+        assert_eq!(probes.enter(BLOCK_WRITE_BLOCKS, &[]), None);
+        NOW.store(500_007_000, Ordering::Relaxed);
+        assert!(probes.enter(BLOCK_WRITE_BLOCKS, &[]).is_some());
+
+        let status = probes.status();
+        assert_eq!((status.enabled, status.rate, status.limited, status.dropped), (BLOCK_WRITE_BLOCKS.bit(), 2, 1, 0));
+        let (records, lost) = probes.read(1, 16).unwrap();
+        assert_eq!(lost, 0);
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].argc, MAX_ARGS as u8);
+        assert_eq!(records[0].args, [16, 2, 1024, 0]);
+        assert_eq!((records[1].kind, records[1].args[0], records[1].args[1]), (RecordKind::Exit, 1024, 5_000));
+        assert_eq!(records[3].kind, RecordKind::Error);
+        assert_eq!(records[3].error.as_str(), "IoError");
+        assert_eq!(records[4].sequence, 5);
+    }
+
+    #[test]
+    fn test_readers_learn_what_they_missed() {
+        let probes = Probes::new(clock);
+        probes.enable(u32::MAX, 0);
+        for lba in 0..RING_SIZE as u64 + 10 {
+            probes.enter(BLOCK_READ_BLOCKS, &[lba]);
+        }
+        let (records, lost) = probes.read(1, 4).unwrap();
+        assert_eq!(lost, 10);
+        assert_eq!(records[0].args[0], 10);
+        let (records, lost) = probes.read(RING_SIZE as u64 + 11, 4).unwrap();
+        assert_eq!((records.len(), lost), (0, 0));
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * Orion Operating System - Probe Values
+ *
+ * How arguments and return values are captured. A probe keeps a 64-bit
+ * word per argument: integers as they are, buffers by their length and
+ * byte arrays such as MAC addresses by their first eight bytes. A call
+ * that failed keeps the name of its error instead of a value.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::fmt::{self, Write};
+
+/// Bytes kept of the name of an error
+pub const ERROR_NAME_SIZE: usize = 16;
+
+/// Value captured for a probe argument
+pub trait ProbeArg {
+    fn probe_value(&self) -> u64;
+}
+
+macro_rules! integer_args {
+    ($($ty:ty),*) => {
+        $(impl ProbeArg for $ty {
+            fn probe_value(&self) -> u64 {
+                *self as u64
+            }
+        })*
+    };
+}
+
+integer_args!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, bool);
+
+impl ProbeArg for () {
+    fn probe_value(&self) -> u64 {
+        0
+    }
+}
+
+impl ProbeArg for [u8] {
+    fn probe_value(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+impl<const N: usize> ProbeArg for [u8; N] {
+    fn probe_value(&self) -> u64 {
+        let mut word = [0u8; 8];
+        let count = N.min(8);
+        word[..count].copy_from_slice(&self[..count]);
+        u64::from_le_bytes(word)
+    }
+}
+
+impl<T: ProbeArg + ?Sized> ProbeArg for &T {
+    fn probe_value(&self) -> u64 {
+        (**self).probe_value()
+    }
+}
+
+impl<T: ProbeArg + ?Sized> ProbeArg for &mut T {
+    fn probe_value(&self) -> u64 {
+        (**self).probe_value()
+    }
+}
+
+/// Debug name of an error, truncated to ERROR_NAME_SIZE bytes and padded
+/// with NULs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorName(pub [u8; ERROR_NAME_SIZE]);
+
+impl ErrorName {
+    pub fn of(error: &dyn fmt::Debug) -> Self {
+        struct Truncated {
+            name: [u8; ERROR_NAME_SIZE],
+            length: usize,
+        }
+
+        impl Write for Truncated {
+            fn write_str(&mut self, text: &str) -> fmt::Result {
+                for &byte in text.as_bytes() {
+                    // Keep the variant name, not its fields
+                    if self.length == ERROR_NAME_SIZE || matches!(byte, b'(' | b' ' | b'{') {
+                        return Err(fmt::Error);
+                    }
+                    self.name[self.length] = byte;
+                    self.length += 1;
+                }
+                Ok(())
+            }
+        }
+
+        let mut truncated = Truncated { name: [0; ERROR_NAME_SIZE], length: 0 };
+        let _ = write!(truncated, "{:?}", error);
+        ErrorName(truncated.name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        let length = self.0.iter().position(|&byte| byte == 0).unwrap_or(ERROR_NAME_SIZE);
+        core::str::from_utf8(&self.0[..length]).unwrap_or("?")
+    }
+}
+
+/// What a probed call returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Value(u64),
+    Error(ErrorName),
+}
+
+/// Outcome captured for a probe return value
+pub trait ProbeReturn {
+    fn probe_return(&self) -> Outcome;
+}
+
+impl<T: ProbeArg, E: fmt::Debug> ProbeReturn for Result<T, E> {
+    fn probe_return(&self) -> Outcome {
+        match self {
+            Ok(value) => Outcome::Value(value.probe_value()),
+            Err(error) => Outcome::Error(ErrorName::of(error)),
+        }
+    }
+}
+
+macro_rules! value_returns {
+    ($($ty:ty),*) => {
+        $(impl ProbeReturn for $ty {
+            fn probe_return(&self) -> Outcome {
+                Outcome::Value(self.probe_value())
+            }
+        })*
+    };
+}
+
+value_returns!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, bool, ());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum DriverError {
+        #[allow(dead_code)]
+        Timeout {
+            lba: u64,
+        },
+        VeryLongErrorVariantName,
+    }
+
+    #[test]
+    fn test_captures_values_and_errors() {
+        let mut buffer = [0u8; 512];
+        let slice: &mut [u8] = &mut buffer;
+        assert_eq!(ProbeArg::probe_value(&slice), 512);
+        assert_eq!([0x52u8, 0x54, 0, 0x12, 0x34, 0x56].probe_value(), 0x5634_1200_5452);
+        assert_eq!((-1i32).probe_value(), u64::MAX);
+
+        let ok: Result<usize, DriverError> = Ok(4096);
+        assert_eq!(ok.probe_return(), Outcome::Value(4096));
+        let failed: Result<usize, DriverError> = Err(DriverError::Timeout { lba: 8 });
+        match failed.probe_return() {
+            Outcome::Error(name) => assert_eq!(name.as_str(), "Timeout"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match Err::<(), _>(DriverError::VeryLongErrorVariantName).probe_return() {
+            Outcome::Error(name) => assert_eq!(name.as_str(), "VeryLongErrorVar"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+/*
+ * Orion Operating System - Server Protocols
+ *
+ * What the IPC protocols of the servers and drivers have in common: the
+ * capability rights requests are checked against (see rights.rs), and
+ * the reply layout, a little-endian `status: i32` (0 or a negative
+ * errno) followed by the payload of the request.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+pub mod rights;
+
+/// Build a reply carrying `status` followed by `payload`
+pub fn reply(status: i32, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + payload.len());
+    out.extend_from_slice(&status.to_le_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_puts_the_status_first() {
+        assert_eq!(reply(-11, &[7, 8]), [0xF5, 0xFF, 0xFF, 0xFF, 7, 8]);
+        assert_eq!(reply(0, &[]), [0, 0, 0, 0]);
+    }
+}
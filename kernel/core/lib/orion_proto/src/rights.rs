@@ -0,0 +1,30 @@
+/*
+ * Orion Operating System - Capability Rights
+ *
+ * Rights of a capability, as capabilities.c defines them. Servers check
+ * the rights of the capability a request arrives with against these
+ * before serving it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+pub const CAP_READ: u64 = 1 << 0;
+pub const CAP_WRITE: u64 = 1 << 1;
+pub const CAP_EXEC: u64 = 1 << 2;
+pub const CAP_GRANT: u64 = 1 << 3;
+pub const CAP_REVOKE: u64 = 1 << 4;
+pub const CAP_DELETE: u64 = 1 << 5;
+pub const CAP_CREATE: u64 = 1 << 6;
+pub const CAP_MODIFY: u64 = 1 << 7;
+pub const CAP_TRAVERSE: u64 = 1 << 8;
+pub const CAP_BIND: u64 = 1 << 9;
+pub const CAP_LISTEN: u64 = 1 << 10;
+pub const CAP_CONNECT: u64 = 1 << 11;
+pub const CAP_DEBUG: u64 = 1 << 12;
+pub const CAP_ADMIN: u64 = 1 << 13;
+pub const CAP_IMMORTAL: u64 = 1 << 14;
+pub const CAP_DELEGATABLE: u64 = 1 << 15;
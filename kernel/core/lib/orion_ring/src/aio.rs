@@ -0,0 +1,381 @@
+/*
+ * Orion Operating System - POSIX Asynchronous I/O
+ *
+ * aio_read, aio_write, aio_fsync, lio_listio and their companions mapped
+ * onto a registered ring. Each request takes a slot whose index and
+ * generation form the user data cookie, so a completion for a request
+ * that was already returned can never be credited to its successor.
+ * Buffers live in the data area of the ring and are named by offset.
+ *
+ * Errors follow POSIX: positive errno values, with EINPROGRESS from
+ * aio_error while a request is outstanding.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::protocol::RingRequest;
+use crate::ring::{ApplicationRing, RingError, Sqe, OP_FSYNC, OP_READ, OP_WRITE};
+
+// errno values
+pub const EIO: i32 = 5;
+pub const EAGAIN: i32 = 11;
+pub const EINVAL: i32 = 22;
+pub const EINPROGRESS: i32 = 115;
+
+// lio_listio opcodes
+pub const LIO_READ: u32 = 0;
+pub const LIO_WRITE: u32 = 1;
+pub const LIO_NOP: u32 = 2;
+
+// aio_cancel results
+pub const AIO_CANCELED: i32 = 0;
+pub const AIO_NOTCANCELED: i32 = 1;
+pub const AIO_ALLDONE: i32 = 2;
+
+/// Request/reply channel to the server the ring is registered with
+pub trait RingChannel {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Asynchronous I/O control block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AioCb {
+    pub fildes: u32,
+    pub offset: u64,
+    /// Offset of the buffer in the ring data area
+    pub buffer: u64,
+    pub nbytes: u32,
+    /// LIO_READ, LIO_WRITE or LIO_NOP, used by lio_listio only
+    pub lio_opcode: u32,
+}
+
+/// Handle of a submitted request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AioRequest {
+    slot: u32,
+    generation: u32,
+}
+
+impl AioRequest {
+    fn user_data(&self) -> u64 {
+        (self.generation as u64) << 32 | self.slot as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Free,
+    InProgress,
+    Done(i32),
+}
+
+struct Slot {
+    generation: u32,
+    state: State,
+}
+
+pub struct AioContext<C: RingChannel> {
+    ring: ApplicationRing,
+    ring_id: u32,
+    channel: C,
+    slots: Vec<Slot>,
+}
+
+impl<C: RingChannel> AioContext<C> {
+    /// Use `ring`, registered as `ring_id` with the server behind
+    /// `channel`, for at most `max_requests` outstanding requests
+    pub fn new(ring: ApplicationRing, ring_id: u32, channel: C, max_requests: usize) -> Self {
+        let slots = (0..max_requests).map(|_| Slot { generation: 0, state: State::Free }).collect();
+        Self { ring, ring_id, channel, slots }
+    }
+
+    pub fn ring(&self) -> &ApplicationRing {
+        &self.ring
+    }
+
+    /// Ring the doorbell; the server has completed what it consumed when
+    /// this returns
+    fn enter(&mut self) -> Result<u32, i32> {
+        let request = RingRequest::Enter { ring: self.ring_id, to_submit: 0 }.encode();
+        let reply = self.channel.call(&request).ok_or(EIO)?;
+        if reply.len() < 4 {
+            return Err(EIO);
+        }
+        let status = i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]]);
+        if status < 0 {
+            return Err(-status);
+        }
+        let consumed = reply.get(4..8).ok_or(EIO)?;
+        Ok(u32::from_le_bytes([consumed[0], consumed[1], consumed[2], consumed[3]]))
+    }
+
+    fn queue(&mut self, opcode: u8, cb: &AioCb) -> Result<AioRequest, i32> {
+        let slot = self.slots.iter().position(|slot| slot.state == State::Free).ok_or(EAGAIN)?;
+        let request = AioRequest { slot: slot as u32, generation: self.slots[slot].generation };
+        let sqe = Sqe {
+            opcode,
+            handle: cb.fildes,
+            offset: cb.offset,
+            buffer: cb.buffer,
+            length: cb.nbytes,
+            user_data: request.user_data(),
+            ..Sqe::default()
+        };
+
+        let mut submitted = self.ring.submit(&sqe);
+        if submitted == Err(RingError::Full) && self.enter().is_ok() {
+            self.reap();
+            submitted = self.ring.submit(&sqe);
+        }
+        submitted.map_err(|_| EAGAIN)?;
+        self.slots[slot].state = State::InProgress;
+        Ok(request)
+    }
+
+    fn submit(&mut self, opcode: u8, cb: &AioCb) -> Result<AioRequest, i32> {
+        let request = self.queue(opcode, cb)?;
+        // The entry is queued either way; a failed doorbell is rung again
+        // by aio_suspend
+        let _ = self.enter();
+        Ok(request)
+    }
+
+    pub fn aio_read(&mut self, cb: &AioCb) -> Result<AioRequest, i32> {
+        self.submit(OP_READ, cb)
+    }
+
+    pub fn aio_write(&mut self, cb: &AioCb) -> Result<AioRequest, i32> {
+        self.submit(OP_WRITE, cb)
+    }
+
+    pub fn aio_fsync(&mut self, cb: &AioCb) -> Result<AioRequest, i32> {
+        self.submit(OP_FSYNC, cb)
+    }
+
+    /// Queue every control block and ring the doorbell once; LIO_NOP
+    /// entries yield Ok(None)
+    pub fn lio_listio(&mut self, list: &[AioCb]) -> Vec<Result<Option<AioRequest>, i32>> {
+        let results = list
+            .iter()
+            .map(|cb| match cb.lio_opcode {
+                LIO_READ => self.queue(OP_READ, cb).map(Some),
+                LIO_WRITE => self.queue(OP_WRITE, cb).map(Some),
+                LIO_NOP => Ok(None),
+                _ => Err(EINVAL),
+            })
+            .collect();
+        let _ = self.enter();
+        results
+    }
+
+    // Credit posted completions to their slots
+    fn reap(&mut self) {
+        while let Some(cqe) = self.ring.next_completion() {
+            let index = cqe.user_data as u32 as usize;
+            let generation = (cqe.user_data >> 32) as u32;
+            if let Some(slot) = self.slots.get_mut(index) {
+                if slot.generation == generation && slot.state == State::InProgress {
+                    slot.state = State::Done(cqe.result);
+                }
+            }
+        }
+    }
+
+    fn state(&mut self, request: &AioRequest) -> Option<State> {
+        self.reap();
+        let slot = self.slots.get(request.slot as usize)?;
+        if slot.generation != request.generation || slot.state == State::Free {
+            return None;
+        }
+        Some(slot.state)
+    }
+
+    /// 0 once completed successfully, EINPROGRESS while outstanding, or
+    /// the error of the request
+    pub fn aio_error(&mut self, request: &AioRequest) -> i32 {
+        match self.state(request) {
+            Some(State::InProgress) => EINPROGRESS,
+            Some(State::Done(result)) if result < 0 => -result,
+            Some(_) => 0,
+            None => EINVAL,
+        }
+    }
+
+    /// Final result of a completed request, releasing it
+    pub fn aio_return(&mut self, request: &AioRequest) -> Result<i32, i32> {
+        let result = match self.state(request) {
+            Some(State::Done(result)) => result,
+            _ => return Err(EINVAL),
+        };
+        let slot = &mut self.slots[request.slot as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.state = State::Free;
+        if result < 0 {
+            Err(-result)
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Wait until one of `list` has completed. Servers complete what they
+    /// consume before answering the doorbell, so this fails with EAGAIN
+    /// instead of blocking when no listed request can make progress.
+    pub fn aio_suspend(&mut self, list: &[AioRequest]) -> Result<(), i32> {
+        loop {
+            if list.iter().any(|request| !matches!(self.state(request), Some(State::InProgress))) {
+                return Ok(());
+            }
+            if self.ring.pending() == 0 || self.enter()? == 0 {
+                return Err(EAGAIN);
+            }
+        }
+    }
+
+    /// Entries handed to the ring cannot be withdrawn
+    pub fn aio_cancel(&mut self, request: &AioRequest) -> i32 {
+        match self.state(request) {
+            Some(State::InProgress) => AIO_NOTCANCELED,
+            _ => AIO_ALLDONE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring::{RingLayout, ServerRing};
+    use alloc::vec;
+
+    // Server holding one file, with doorbells that can be ignored
+    struct FileServer {
+        ring: ServerRing,
+        file: Vec<u8>,
+        doorbells: u32,
+        deaf: bool,
+    }
+
+    impl RingChannel for FileServer {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            self.doorbells += 1;
+            let to_submit = match RingRequest::decode(request)? {
+                RingRequest::Enter { to_submit, .. } if !self.deaf => to_submit,
+                _ => return None,
+            };
+            let file = &mut self.file;
+            let consumed = self.ring.process(to_submit, |ring, sqe| {
+                let start = sqe.offset as usize;
+                if sqe.handle != 3 || start > file.len() {
+                    return -9;
+                }
+                let length = (sqe.length as usize).min(file.len() - start);
+                match sqe.opcode {
+                    OP_READ => match ring.write_data(sqe.buffer, &file[start..start + length]) {
+                        Ok(()) => length as i32,
+                        Err(error) => error.status(),
+                    },
+                    OP_WRITE => match ring.read_data(sqe.buffer, &mut file[start..start + length]) {
+                        Ok(()) => length as i32,
+                        Err(error) => error.status(),
+                    },
+                    _ => 0,
+                }
+            });
+            let mut reply = vec![0u8; 4];
+            reply.extend_from_slice(&consumed.ok()?.to_le_bytes());
+            Some(reply)
+        }
+    }
+
+    fn context(memory: &mut Vec<u64>, sq_entries: u32, max_requests: usize) -> AioContext<FileServer> {
+        let layout = RingLayout::new(sq_entries, sq_entries).unwrap();
+        *memory = vec![0u64; layout.region_size(256).div_ceil(8)];
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        let ring = unsafe { ApplicationRing::init(base, size, layout) }.unwrap();
+        let server = FileServer {
+            ring: unsafe { ServerRing::attach(base, size) }.unwrap(),
+            file: b"0123456789".to_vec(),
+            doorbells: 0,
+            deaf: false,
+        };
+        AioContext::new(ring, 1, server, max_requests)
+    }
+
+    #[test]
+    fn test_reads_and_writes() {
+        let mut memory = Vec::new();
+        let mut aio = context(&mut memory, 4, 4);
+
+        let read = aio.aio_read(&AioCb { fildes: 3, offset: 2, buffer: 0, nbytes: 4, ..AioCb::default() }).unwrap();
+        assert_eq!(aio.aio_error(&read), 0);
+        assert_eq!(aio.aio_return(&read), Ok(4));
+        let mut data = [0u8; 4];
+        aio.ring().read_data(0, &mut data).unwrap();
+        assert_eq!(&data, b"2345");
+        // Returned requests are gone, even once the slot is reused
+        assert_eq!(aio.aio_return(&read), Err(EINVAL));
+
+        aio.ring().write_data(64, b"ab").unwrap();
+        let write = aio.aio_write(&AioCb { fildes: 3, offset: 8, buffer: 64, nbytes: 2, ..AioCb::default() }).unwrap();
+        assert_eq!(write.slot, read.slot);
+        assert_eq!(aio.aio_error(&read), EINVAL);
+        assert_eq!(aio.aio_return(&write), Ok(2));
+        assert_eq!(&aio.channel.file, b"01234567ab");
+
+        let bad = aio.aio_fsync(&AioCb { fildes: 4, ..AioCb::default() }).unwrap();
+        assert_eq!(aio.aio_error(&bad), 9);
+        assert_eq!(aio.aio_cancel(&bad), AIO_ALLDONE);
+        assert_eq!(aio.aio_return(&bad), Err(9));
+    }
+
+    #[test]
+    fn test_batches_with_one_doorbell() {
+        let mut memory = Vec::new();
+        let mut aio = context(&mut memory, 4, 8);
+        let list = [
+            AioCb { fildes: 3, offset: 0, buffer: 0, nbytes: 2, lio_opcode: LIO_READ },
+            AioCb { lio_opcode: LIO_NOP, ..AioCb::default() },
+            AioCb { fildes: 3, offset: 4, buffer: 8, nbytes: 2, lio_opcode: LIO_READ },
+            AioCb { lio_opcode: 9, ..AioCb::default() },
+        ];
+        let results = aio.lio_listio(&list);
+        assert_eq!(aio.channel.doorbells, 1);
+        assert_eq!(results[1], Ok(None));
+        assert_eq!(results[3], Err(EINVAL));
+
+        let first = results[0].unwrap().unwrap();
+        let second = results[2].unwrap().unwrap();
+        assert_eq!(aio.aio_suspend(&[first, second]), Ok(()));
+        assert_eq!((aio.aio_return(&first), aio.aio_return(&second)), (Ok(2), Ok(2)));
+        let mut data = [0u8; 2];
+        aio.ring().read_data(8, &mut data).unwrap();
+        assert_eq!(&data, b"45");
+    }
+
+    #[test]
+    fn test_reports_outstanding_requests() {
+        let mut memory = Vec::new();
+        let mut aio = context(&mut memory, 2, 3);
+        aio.channel.deaf = true;
+
+        let cb = AioCb { fildes: 3, nbytes: 1, ..AioCb::default() };
+        let first = aio.aio_read(&cb).unwrap();
+        let second = aio.aio_read(&cb).unwrap();
+        assert_eq!(aio.aio_error(&first), EINPROGRESS);
+        assert_eq!(aio.aio_cancel(&first), AIO_NOTCANCELED);
+        // Submission queue full and the server does not answer
+        assert_eq!(aio.aio_read(&cb), Err(EAGAIN));
+        assert_eq!(aio.aio_suspend(&[first]), Err(EIO));
+
+        aio.channel.deaf = false;
+        assert_eq!(aio.aio_suspend(&[second]), Ok(()));
+        assert_eq!(aio.aio_return(&first), Ok(1));
+        assert_eq!(aio.aio_return(&second), Ok(1));
+    }
+}
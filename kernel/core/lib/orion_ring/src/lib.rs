@@ -0,0 +1,29 @@
+/*
+ * Orion Operating System - Asynchronous I/O Rings
+ *
+ * Submission/completion rings in a shared memory object, so that an
+ * application can queue many file or socket operations and hand them to
+ * the fs or net server with a single IPC doorbell instead of one round
+ * trip per call. The application owns the submission tail and the
+ * completion head, the server the other two indices; completions carry
+ * the user data cookie of the submission they answer. POSIX aio is
+ * provided on top of the rings.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod aio;
+pub mod protocol;
+pub mod ring;
+
+pub use aio::{AioCb, AioContext, AioRequest, RingChannel};
+pub use protocol::RingRequest;
+pub use ring::{ApplicationRing, Cqe, RingError, RingLayout, ServerRing, Sqe};
@@ -0,0 +1,94 @@
+/*
+ * Orion Operating System - Ring Protocol
+ *
+ * IPC requests that set up and drive rings. The fs and net servers accept
+ * them next to their own requests, so the opcodes are kept clear of the
+ * range either server uses. Same little-endian framing as every server:
+ * a 32-bit opcode first, replies start with a 32-bit signed status.
+ *
+ *   REGISTER    (shared memory capability) -> ring:u32
+ *   ENTER       ring:u32 to_submit:u32     -> consumed:u32
+ *   UNREGISTER  ring:u32                   -> (empty)
+ *
+ * REGISTER transfers the capability of the shared memory object holding
+ * the ring with the message; the server maps it and checks the header the
+ * application laid out. ENTER is the doorbell: the server consumes up to
+ * `to_submit` entries (0 for all queued) and has posted their completions
+ * by the time it replies. Rings belong to the process that registered
+ * them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_RING_REGISTER: u32 = 0x100;
+pub const OP_RING_ENTER: u32 = 0x101;
+pub const OP_RING_UNREGISTER: u32 = 0x102;
+
+/// Rings one process may register with a server
+pub const MAX_RINGS_PER_PROCESS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingRequest {
+    Register,
+    Enter { ring: u32, to_submit: u32 },
+    Unregister { ring: u32 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl RingRequest {
+    /// Decode a ring request; None for anything else, so servers can fall
+    /// back to their own protocol
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_RING_REGISTER => Some(RingRequest::Register),
+            OP_RING_ENTER => Some(RingRequest::Enter { ring: read_u32(data, 4)?, to_submit: read_u32(data, 8)? }),
+            OP_RING_UNREGISTER => Some(RingRequest::Unregister { ring: read_u32(data, 4)? }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12);
+        match *self {
+            RingRequest::Register => out.extend_from_slice(&OP_RING_REGISTER.to_le_bytes()),
+            RingRequest::Enter { ring, to_submit } => {
+                out.extend_from_slice(&OP_RING_ENTER.to_le_bytes());
+                out.extend_from_slice(&ring.to_le_bytes());
+                out.extend_from_slice(&to_submit.to_le_bytes());
+            }
+            RingRequest::Unregister { ring } => {
+                out.extend_from_slice(&OP_RING_UNREGISTER.to_le_bytes());
+                out.extend_from_slice(&ring.to_le_bytes());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_round_trip() {
+        for request in
+            [RingRequest::Register, RingRequest::Enter { ring: 3, to_submit: 0 }, RingRequest::Unregister { ring: 3 }]
+        {
+            assert_eq!(RingRequest::decode(&request.encode()), Some(request));
+        }
+        let enter = RingRequest::Enter { ring: 1, to_submit: 8 }.encode();
+        assert_eq!(RingRequest::decode(&enter[..8]), None);
+        assert_eq!(RingRequest::decode(&4u32.to_le_bytes()), None);
+    }
+}
@@ -0,0 +1,570 @@
+/*
+ * Orion Operating System - Ring Layout
+ *
+ * Layout of a ring region, all fields little-endian:
+ *
+ *   0x00  magic:u32 version:u32 sq_entries:u32 cq_entries:u32
+ *   0x10  sq_head:u32 sq_tail:u32 cq_head:u32 cq_tail:u32
+ *   0x20  cq_overflow:u32, reserved up to 0x40
+ *   0x40  submission entries (SQE_SIZE bytes each)
+ *         completion entries (CQE_SIZE bytes each)
+ *         data area, 64-byte aligned, up to the end of the region
+ *
+ * Indices run freely and wrap at 2^32; entry counts are powers of two so
+ * the slot is the index masked. Buffers are named by their offset in the
+ * data area, never by address, since each side maps the region wherever
+ * it likes.
+ *
+ * Both sides keep a private copy of the indices they own and only read
+ * the peer's, so a misbehaving application can corrupt its own queues but
+ * never make the server read or write outside the region.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub const RING_MAGIC: u32 = 0x474E_524F; // "ORNG"
+pub const RING_VERSION: u32 = 1;
+
+pub const HEADER_SIZE: usize = 0x40;
+pub const SQE_SIZE: usize = 40;
+pub const CQE_SIZE: usize = 16;
+
+/// Largest queue accepted by the servers
+pub const MAX_ENTRIES: u32 = 4096;
+
+const DATA_ALIGN: usize = 64;
+
+// Header fields
+const MAGIC: usize = 0x00;
+const VERSION: usize = 0x04;
+const SQ_ENTRIES: usize = 0x08;
+const CQ_ENTRIES: usize = 0x0C;
+const SQ_HEAD: usize = 0x10;
+const SQ_TAIL: usize = 0x14;
+const CQ_HEAD: usize = 0x18;
+const CQ_TAIL: usize = 0x1C;
+const CQ_OVERFLOW: usize = 0x20;
+
+// Submission opcodes
+pub const OP_NOP: u8 = 0;
+/// Read `length` bytes at `offset` of file `handle` into the buffer
+pub const OP_READ: u8 = 1;
+/// Write `length` bytes of the buffer at `offset` of file `handle`
+pub const OP_WRITE: u8 = 2;
+pub const OP_FSYNC: u8 = 3;
+/// Close file or socket `handle`
+pub const OP_CLOSE: u8 = 4;
+/// Send `length` bytes of the buffer on socket `handle`
+pub const OP_SEND: u8 = 5;
+/// Receive up to `length` bytes from socket `handle` into the buffer
+pub const OP_RECV: u8 = 6;
+
+/// READ and WRITE offset meaning "at the current file position"
+pub const OFFSET_CURRENT: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingError {
+    /// Entry counts not powers of two, zero or above MAX_ENTRIES
+    InvalidLayout,
+    /// Region smaller than the layout or not 8-byte aligned
+    BadRegion,
+    /// Header not written by this version of the ring code
+    BadMagic,
+    /// No free slot in the queue
+    Full,
+    /// The peer moved an index past what it may
+    Corrupt,
+    /// Buffer outside the data area
+    OutOfBounds,
+}
+
+impl RingError {
+    /// Negative errno reported for the error
+    pub fn status(&self) -> i32 {
+        match self {
+            RingError::Full => -11,
+            RingError::OutOfBounds => -14,
+            RingError::Corrupt => -5,
+            _ => -22,
+        }
+    }
+}
+
+/// Submission queue entry
+///
+///   0 opcode:u8 1 flags:u8 4 handle:u32 8 offset:u64 16 buffer:u64
+///   24 length:u32 32 user_data:u64
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub handle: u32,
+    pub offset: u64,
+    /// Offset of the buffer in the data area
+    pub buffer: u64,
+    pub length: u32,
+    /// Cookie returned in the completion
+    pub user_data: u64,
+}
+
+impl Sqe {
+    fn encode(&self) -> [u8; SQE_SIZE] {
+        let mut bytes = [0u8; SQE_SIZE];
+        bytes[0] = self.opcode;
+        bytes[1] = self.flags;
+        bytes[4..8].copy_from_slice(&self.handle.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.buffer.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.length.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.user_data.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8; SQE_SIZE]) -> Self {
+        Self {
+            opcode: bytes[0],
+            flags: bytes[1],
+            handle: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            offset: read_u64(&bytes[8..16]),
+            buffer: read_u64(&bytes[16..24]),
+            length: u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]),
+            user_data: read_u64(&bytes[32..40]),
+        }
+    }
+}
+
+/// Completion queue entry
+///
+///   0 user_data:u64 8 result:i32 12 flags:u32
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cqe {
+    pub user_data: u64,
+    /// Bytes transferred, or a negative errno
+    pub result: i32,
+    pub flags: u32,
+}
+
+impl Cqe {
+    fn encode(&self) -> [u8; CQE_SIZE] {
+        let mut bytes = [0u8; CQE_SIZE];
+        bytes[0..8].copy_from_slice(&self.user_data.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.result.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.flags.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8; CQE_SIZE]) -> Self {
+        Self {
+            user_data: read_u64(&bytes[0..8]),
+            result: i32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            flags: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        }
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(bytes);
+    u64::from_le_bytes(value)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingLayout {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+}
+
+impl RingLayout {
+    /// The completion queue must hold at least a full submission queue
+    pub fn new(sq_entries: u32, cq_entries: u32) -> Result<Self, RingError> {
+        let valid = |entries: u32| entries.is_power_of_two() && entries <= MAX_ENTRIES;
+        if !valid(sq_entries) || !valid(cq_entries) || cq_entries < sq_entries {
+            return Err(RingError::InvalidLayout);
+        }
+        Ok(Self { sq_entries, cq_entries })
+    }
+
+    pub fn sq_offset(&self) -> usize {
+        HEADER_SIZE
+    }
+
+    pub fn cq_offset(&self) -> usize {
+        self.sq_offset() + self.sq_entries as usize * SQE_SIZE
+    }
+
+    pub fn data_offset(&self) -> usize {
+        let end = self.cq_offset() + self.cq_entries as usize * CQE_SIZE;
+        (end + DATA_ALIGN - 1) & !(DATA_ALIGN - 1)
+    }
+
+    /// Region size needed for `data_size` bytes of buffers
+    pub fn region_size(&self, data_size: usize) -> usize {
+        self.data_offset() + data_size
+    }
+}
+
+// Mapped ring region shared by both ends
+struct Region {
+    base: NonNull<u8>,
+    size: usize,
+    layout: RingLayout,
+}
+
+impl Region {
+    unsafe fn new(base: *mut u8, size: usize, layout: RingLayout) -> Result<Self, RingError> {
+        let base = NonNull::new(base).ok_or(RingError::BadRegion)?;
+        if !(base.as_ptr() as usize).is_multiple_of(8) || size < layout.region_size(0) {
+            return Err(RingError::BadRegion);
+        }
+        Ok(Self { base, size, layout })
+    }
+
+    fn field(&self, offset: usize) -> &AtomicU32 {
+        // Header fields are 4-byte aligned inside a region checked to be 8-byte aligned
+        unsafe { &*(self.base.as_ptr().add(offset) as *const AtomicU32) }
+    }
+
+    fn read(&self, offset: usize, out: &mut [u8]) {
+        debug_assert!(offset + out.len() <= self.size);
+        unsafe { ptr::copy_nonoverlapping(self.base.as_ptr().add(offset), out.as_mut_ptr(), out.len()) }
+    }
+
+    fn write(&self, offset: usize, data: &[u8]) {
+        debug_assert!(offset + data.len() <= self.size);
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), self.base.as_ptr().add(offset), data.len()) }
+    }
+
+    fn data_range(&self, buffer: u64, length: usize) -> Result<usize, RingError> {
+        let data_size = (self.size - self.layout.data_offset()) as u64;
+        let end = buffer.checked_add(length as u64).ok_or(RingError::OutOfBounds)?;
+        if end > data_size {
+            return Err(RingError::OutOfBounds);
+        }
+        Ok(self.layout.data_offset() + buffer as usize)
+    }
+
+    fn read_data(&self, buffer: u64, out: &mut [u8]) -> Result<(), RingError> {
+        let offset = self.data_range(buffer, out.len())?;
+        self.read(offset, out);
+        Ok(())
+    }
+
+    fn write_data(&self, buffer: u64, data: &[u8]) -> Result<(), RingError> {
+        let offset = self.data_range(buffer, data.len())?;
+        self.write(offset, data);
+        Ok(())
+    }
+
+    fn data_size(&self) -> usize {
+        self.size - self.layout.data_offset()
+    }
+}
+
+/// Application end: submits entries and reaps completions
+pub struct ApplicationRing {
+    region: Region,
+    sq_tail: u32,
+    cq_head: u32,
+}
+
+impl ApplicationRing {
+    /// Lay out a fresh ring in a region the application mapped
+    ///
+    /// # Safety
+    /// `base` must be valid for reads and writes of `size` bytes for as
+    /// long as the ring is used, and not be used for anything else.
+    pub unsafe fn init(base: *mut u8, size: usize, layout: RingLayout) -> Result<Self, RingError> {
+        let region = Region::new(base, size, layout)?;
+        region.write(0, &[0u8; HEADER_SIZE]);
+        region.field(MAGIC).store(RING_MAGIC, Ordering::Relaxed);
+        region.field(VERSION).store(RING_VERSION, Ordering::Relaxed);
+        region.field(SQ_ENTRIES).store(layout.sq_entries, Ordering::Relaxed);
+        region.field(CQ_ENTRIES).store(layout.cq_entries, Ordering::Release);
+        Ok(Self { region, sq_tail: 0, cq_head: 0 })
+    }
+
+    pub fn layout(&self) -> RingLayout {
+        self.region.layout
+    }
+
+    /// Queue one entry; the server sees it after the next doorbell
+    pub fn submit(&mut self, sqe: &Sqe) -> Result<(), RingError> {
+        let layout = self.region.layout;
+        let head = self.region.field(SQ_HEAD).load(Ordering::Acquire);
+        if self.sq_tail.wrapping_sub(head) >= layout.sq_entries {
+            return Err(RingError::Full);
+        }
+        let slot = (self.sq_tail & (layout.sq_entries - 1)) as usize;
+        self.region.write(layout.sq_offset() + slot * SQE_SIZE, &sqe.encode());
+        self.sq_tail = self.sq_tail.wrapping_add(1);
+        self.region.field(SQ_TAIL).store(self.sq_tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Entries submitted but not yet consumed by the server
+    pub fn pending(&self) -> u32 {
+        self.sq_tail.wrapping_sub(self.region.field(SQ_HEAD).load(Ordering::Acquire))
+    }
+
+    /// Next completion posted by the server
+    pub fn next_completion(&mut self) -> Option<Cqe> {
+        let layout = self.region.layout;
+        let tail = self.region.field(CQ_TAIL).load(Ordering::Acquire);
+        if tail == self.cq_head {
+            return None;
+        }
+        let slot = (self.cq_head & (layout.cq_entries - 1)) as usize;
+        let mut bytes = [0u8; CQE_SIZE];
+        self.region.read(layout.cq_offset() + slot * CQE_SIZE, &mut bytes);
+        self.cq_head = self.cq_head.wrapping_add(1);
+        self.region.field(CQ_HEAD).store(self.cq_head, Ordering::Release);
+        Some(Cqe::decode(&bytes))
+    }
+
+    /// Completions the server could not post because the queue was full
+    pub fn overflow(&self) -> u32 {
+        self.region.field(CQ_OVERFLOW).load(Ordering::Relaxed)
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.region.data_size()
+    }
+
+    pub fn read_data(&self, buffer: u64, out: &mut [u8]) -> Result<(), RingError> {
+        self.region.read_data(buffer, out)
+    }
+
+    pub fn write_data(&self, buffer: u64, data: &[u8]) -> Result<(), RingError> {
+        self.region.write_data(buffer, data)
+    }
+}
+
+/// Server end: consumes entries and posts completions
+pub struct ServerRing {
+    region: Region,
+    sq_head: u32,
+    cq_tail: u32,
+}
+
+impl ServerRing {
+    /// Attach to a ring laid out by the application
+    ///
+    /// # Safety
+    /// `base` must be valid for reads and writes of `size` bytes for as
+    /// long as the ring is used. The application may write to the region
+    /// at any time; nothing read from it is trusted.
+    pub unsafe fn attach(base: *mut u8, size: usize) -> Result<Self, RingError> {
+        let header = Region::new(base, size, RingLayout { sq_entries: 0, cq_entries: 0 })?;
+        if header.field(MAGIC).load(Ordering::Acquire) != RING_MAGIC
+            || header.field(VERSION).load(Ordering::Relaxed) != RING_VERSION
+        {
+            return Err(RingError::BadMagic);
+        }
+        let layout = RingLayout::new(
+            header.field(SQ_ENTRIES).load(Ordering::Relaxed),
+            header.field(CQ_ENTRIES).load(Ordering::Relaxed),
+        )?;
+        let region = Region::new(base, size, layout)?;
+        let sq_head = region.field(SQ_HEAD).load(Ordering::Relaxed);
+        let cq_tail = region.field(CQ_TAIL).load(Ordering::Relaxed);
+        Ok(Self { region, sq_head, cq_tail })
+    }
+
+    pub fn layout(&self) -> RingLayout {
+        self.region.layout
+    }
+
+    fn cq_used(&self) -> Result<u32, RingError> {
+        let head = self.region.field(CQ_HEAD).load(Ordering::Acquire);
+        let used = self.cq_tail.wrapping_sub(head);
+        if used > self.region.layout.cq_entries {
+            return Err(RingError::Corrupt);
+        }
+        Ok(used)
+    }
+
+    /// Next submission, held back while the completion queue has no room
+    /// for its answer
+    pub fn next_submission(&mut self) -> Result<Option<Sqe>, RingError> {
+        let layout = self.region.layout;
+        let tail = self.region.field(SQ_TAIL).load(Ordering::Acquire);
+        let queued = tail.wrapping_sub(self.sq_head);
+        if queued > layout.sq_entries {
+            return Err(RingError::Corrupt);
+        }
+        if queued == 0 || self.cq_used()? >= layout.cq_entries {
+            return Ok(None);
+        }
+
+        let slot = (self.sq_head & (layout.sq_entries - 1)) as usize;
+        let mut bytes = [0u8; SQE_SIZE];
+        self.region.read(layout.sq_offset() + slot * SQE_SIZE, &mut bytes);
+        self.sq_head = self.sq_head.wrapping_add(1);
+        self.region.field(SQ_HEAD).store(self.sq_head, Ordering::Release);
+        Ok(Some(Sqe::decode(&bytes)))
+    }
+
+    pub fn complete(&mut self, cqe: &Cqe) -> Result<(), RingError> {
+        let layout = self.region.layout;
+        if self.cq_used()? >= layout.cq_entries {
+            self.region.field(CQ_OVERFLOW).fetch_add(1, Ordering::Relaxed);
+            return Err(RingError::Full);
+        }
+        let slot = (self.cq_tail & (layout.cq_entries - 1)) as usize;
+        self.region.write(layout.cq_offset() + slot * CQE_SIZE, &cqe.encode());
+        self.cq_tail = self.cq_tail.wrapping_add(1);
+        self.region.field(CQ_TAIL).store(self.cq_tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Run up to `max` submissions (0 for all) through `execute`, posting
+    /// its result as the completion; returns the number consumed
+    pub fn process<F>(&mut self, max: u32, mut execute: F) -> Result<u32, RingError>
+    where
+        F: FnMut(&ServerRing, &Sqe) -> i32,
+    {
+        let mut consumed = 0;
+        while max == 0 || consumed < max {
+            let sqe = match self.next_submission()? {
+                Some(sqe) => sqe,
+                None => break,
+            };
+            let result = execute(self, &sqe);
+            self.complete(&Cqe { user_data: sqe.user_data, result, flags: 0 })?;
+            consumed += 1;
+        }
+        Ok(consumed)
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.region.data_size()
+    }
+
+    pub fn read_data(&self, buffer: u64, out: &mut [u8]) -> Result<(), RingError> {
+        self.region.read_data(buffer, out)
+    }
+
+    pub fn write_data(&self, buffer: u64, data: &[u8]) -> Result<(), RingError> {
+        self.region.write_data(buffer, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn region(layout: RingLayout, data_size: usize) -> Vec<u64> {
+        vec![0u64; layout.region_size(data_size).div_ceil(8)]
+    }
+
+    #[test]
+    fn test_layout_is_validated() {
+        assert_eq!(RingLayout::new(3, 8), Err(RingError::InvalidLayout));
+        assert_eq!(RingLayout::new(16, 8), Err(RingError::InvalidLayout));
+        assert_eq!(RingLayout::new(0, 8), Err(RingError::InvalidLayout));
+        assert_eq!(RingLayout::new(8, 2 * MAX_ENTRIES), Err(RingError::InvalidLayout));
+
+        let layout = RingLayout::new(4, 8).unwrap();
+        assert_eq!(layout.cq_offset(), HEADER_SIZE + 4 * SQE_SIZE);
+        assert_eq!(layout.data_offset() % 64, 0);
+        assert!(layout.data_offset() >= layout.cq_offset() + 8 * CQE_SIZE);
+
+        let mut memory = region(layout, 0);
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        assert!(matches!(unsafe { ServerRing::attach(base, size) }, Err(RingError::BadMagic)));
+        assert!(matches!(unsafe { ApplicationRing::init(base, 16, layout) }, Err(RingError::BadRegion)));
+        assert!(matches!(
+            unsafe { ApplicationRing::init(base.wrapping_add(4), size - 4, layout) },
+            Err(RingError::BadRegion)
+        ));
+    }
+
+    #[test]
+    fn test_submissions_round_trip() {
+        let layout = RingLayout::new(2, 4).unwrap();
+        let mut memory = region(layout, 128);
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        let mut application = unsafe { ApplicationRing::init(base, size, layout) }.unwrap();
+        let mut server = unsafe { ServerRing::attach(base, size) }.unwrap();
+
+        application.write_data(0, b"hello").unwrap();
+        for cookie in 0..2u64 {
+            let sqe = Sqe {
+                opcode: OP_WRITE,
+                handle: 7,
+                offset: 10,
+                buffer: 0,
+                length: 5,
+                user_data: cookie,
+                ..Sqe::default()
+            };
+            application.submit(&sqe).unwrap();
+        }
+        assert_eq!(application.submit(&Sqe::default()), Err(RingError::Full));
+        assert_eq!(application.pending(), 2);
+
+        let consumed = server
+            .process(0, |ring, sqe| {
+                let mut data = [0u8; 5];
+                ring.read_data(sqe.buffer, &mut data).unwrap();
+                assert_eq!(&data, b"hello");
+                sqe.length as i32 + sqe.user_data as i32
+            })
+            .unwrap();
+        assert_eq!(consumed, 2);
+        assert_eq!(application.pending(), 0);
+
+        assert_eq!(application.next_completion(), Some(Cqe { user_data: 0, result: 5, flags: 0 }));
+        assert_eq!(application.next_completion(), Some(Cqe { user_data: 1, result: 6, flags: 0 }));
+        assert_eq!(application.next_completion(), None);
+
+        // Indices keep working after wrapping around the queues
+        for round in 0..10u64 {
+            application.submit(&Sqe { opcode: OP_NOP, user_data: round, ..Sqe::default() }).unwrap();
+            assert_eq!(server.process(1, |_, _| 0).unwrap(), 1);
+            assert_eq!(application.next_completion().unwrap().user_data, round);
+        }
+        assert_eq!(server.write_data(124, b"toolong"), Err(RingError::OutOfBounds));
+        assert_eq!(server.read_data(u64::MAX, &mut [0u8; 2]), Err(RingError::OutOfBounds));
+    }
+
+    #[test]
+    fn test_full_completion_queue_holds_back_submissions() {
+        let layout = RingLayout::new(2, 2).unwrap();
+        let mut memory = region(layout, 0);
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        let mut application = unsafe { ApplicationRing::init(base, size, layout) }.unwrap();
+        let mut server = unsafe { ServerRing::attach(base, size) }.unwrap();
+
+        for cookie in 0..2 {
+            application.submit(&Sqe { user_data: cookie, ..Sqe::default() }).unwrap();
+        }
+        assert_eq!(server.process(0, |_, _| 0).unwrap(), 2);
+        for cookie in 2..4 {
+            application.submit(&Sqe { user_data: cookie, ..Sqe::default() }).unwrap();
+        }
+        // Nothing reaped: the server leaves the new entries queued
+        assert_eq!(server.process(0, |_, _| 0).unwrap(), 0);
+        assert_eq!(server.complete(&Cqe::default()), Err(RingError::Full));
+        assert_eq!(application.overflow(), 1);
+
+        assert!(application.next_completion().is_some());
+        assert_eq!(server.process(0, |_, _| 0).unwrap(), 1);
+
+        // An application moving its tail past the queue is caught
+        unsafe { (base.add(SQ_TAIL) as *mut u32).write(1000) };
+        assert_eq!(server.next_submission(), Err(RingError::Corrupt));
+    }
+}
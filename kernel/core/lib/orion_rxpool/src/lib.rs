@@ -0,0 +1,32 @@
+/*
+ * Orion Operating System - Zero-Copy Receive Buffer Pool
+ *
+ * Receive buffers live in a shared memory object mapped by both a NIC
+ * driver and the network server. The driver points its DMA descriptors at
+ * pool buffers and, once the device has filled one, passes the buffer
+ * index to the server instead of copying the frame into an IPC message.
+ * The server parses the frame where the device wrote it and hands the
+ * index back when done. Every buffer is owned by exactly one side at a
+ * time; ownership moves only through the two index rings of the pool.
+ *
+ * Drivers keep page-sized buffers in a pool per NUMA node and refill
+ * their receive rings from it by watermark (see refill.rs).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod pool;
+pub mod protocol;
+pub mod refill;
+
+pub use pool::{DriverPool, PoolError, PoolLayout, PoolStats, RxCompletion, ServerPool, NODE_ANY, PAGE_BUFFER_SIZE};
+pub use protocol::{DeliverReply, PoolRecord, RxPoolRequest};
+pub use refill::{shm_flags, NodePool, Watermarks};
@@ -0,0 +1,652 @@
+/*
+ * Orion Operating System - Receive Pool Layout
+ *
+ * Layout of a pool region, all fields little-endian:
+ *
+ *   0x00  magic:u32 version:u32 buffer_count:u32 buffer_size:u32
+ *   0x10  fill_head:u32 fill_tail:u32 rx_head:u32 rx_tail:u32
+ *   0x20  node:u32 low:u32 high:u32 exhausted:u32 shortfall:u32
+ *         reserved up to 0x40
+ *   0x40  fill ring (buffer_count indices, FILL_ENTRY_SIZE bytes each)
+ *         rx ring (buffer_count entries, RX_ENTRY_SIZE bytes each)
+ *         buffers, buffer_size bytes each, 64-byte aligned or page
+ *         aligned when they are whole pages, so that in a page aligned
+ *         region none straddles a page
+ *
+ * The fill ring carries free buffers from the server to the driver, the
+ * rx ring filled buffers from the driver to the server. A buffer sits in
+ * at most one ring at a time, so rings of buffer_count entries never
+ * overflow. The driver owns fill_head and rx_tail, the server the other
+ * two. Buffers are named by index, never by address, since each side
+ * maps the region wherever it likes.
+ *
+ * A completion of zero length hands a buffer back unused, which is how a
+ * driver returns buffers the device failed to fill.
+ *
+ * The fields at 0x20 are the driver's PoolStats (see refill.rs), only
+ * read by the server to report them.
+ *
+ * A fresh pool has every buffer queued on the fill ring. The server keeps
+ * its own record of which buffers it holds, so a driver delivering a
+ * buffer twice or naming bytes outside it is caught rather than trusted.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub const POOL_MAGIC: u32 = 0x5058_524F; // "ORXP"
+pub const POOL_VERSION: u32 = 1;
+
+pub const HEADER_SIZE: usize = 0x40;
+pub const FILL_ENTRY_SIZE: usize = 4;
+pub const RX_ENTRY_SIZE: usize = 12;
+
+/// Most buffers in one pool
+pub const MAX_BUFFERS: u32 = 4096;
+/// Largest buffer, enough for a jumbo frame and its device header
+pub const MAX_BUFFER_SIZE: u32 = 16384;
+/// Size of a page-sized buffer
+pub const PAGE_BUFFER_SIZE: u32 = 4096;
+
+const BUFFER_ALIGN: usize = 64;
+const PAGE_SIZE: usize = PAGE_BUFFER_SIZE as usize;
+
+// Header fields
+const MAGIC: usize = 0x00;
+const VERSION: usize = 0x04;
+const BUFFER_COUNT: usize = 0x08;
+const BUFFER_SIZE: usize = 0x0C;
+const FILL_HEAD: usize = 0x10;
+const FILL_TAIL: usize = 0x14;
+const RX_HEAD: usize = 0x18;
+const RX_TAIL: usize = 0x1C;
+const NODE: usize = 0x20;
+const LOW_WATERMARK: usize = 0x24;
+const HIGH_WATERMARK: usize = 0x28;
+const EXHAUSTED: usize = 0x2C;
+const SHORTFALL: usize = 0x30;
+
+/// Node of a pool whose memory was not placed on a given NUMA node
+pub const NODE_ANY: u32 = u32::MAX;
+
+/// The device verified the transport checksum of the frame
+pub const FLAG_CHECKSUM_VALID: u8 = 1 << 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    /// Buffer count not a power of two up to MAX_BUFFERS, or buffer size
+    /// not a multiple of 64 up to MAX_BUFFER_SIZE
+    InvalidLayout,
+    /// Region smaller than the layout or not 8-byte aligned
+    BadRegion,
+    /// Header not written by this version of the pool code
+    BadMagic,
+    /// No room left in the ring
+    Full,
+    /// The peer moved an index past what it may, or handed over a buffer
+    /// it did not own
+    Corrupt,
+    /// Buffer index or bytes outside the pool, or a buffer not held
+    InvalidBuffer,
+}
+
+impl PoolError {
+    /// Negative errno reported for the error
+    pub fn status(&self) -> i32 {
+        match self {
+            PoolError::Full => -11,
+            PoolError::InvalidBuffer => -14,
+            PoolError::Corrupt => -5,
+            _ => -22,
+        }
+    }
+}
+
+/// A filled buffer handed from the driver to the server
+///
+///   0 buffer:u32 4 offset:u16 6 length:u16 8 queue:u8 9 flags:u8
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RxCompletion {
+    pub buffer: u32,
+    /// Start of the frame in the buffer, past any device header
+    pub offset: u16,
+    pub length: u16,
+    /// Receive queue the frame arrived on
+    pub queue: u8,
+    pub flags: u8,
+}
+
+impl RxCompletion {
+    fn encode(&self) -> [u8; RX_ENTRY_SIZE] {
+        let mut bytes = [0u8; RX_ENTRY_SIZE];
+        bytes[0..4].copy_from_slice(&self.buffer.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.length.to_le_bytes());
+        bytes[8] = self.queue;
+        bytes[9] = self.flags;
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RX_ENTRY_SIZE]) -> Self {
+        Self {
+            buffer: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            offset: u16::from_le_bytes([bytes[4], bytes[5]]),
+            length: u16::from_le_bytes([bytes[6], bytes[7]]),
+            queue: bytes[8],
+            flags: bytes[9],
+        }
+    }
+}
+
+/// Where a pool lives and how it has been refilled, published by the
+/// driver in the pool header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// NUMA node the buffers were allocated on, or NODE_ANY
+    pub node: u32,
+    /// Refill watermarks, in buffers posted to the device
+    pub low: u32,
+    pub high: u32,
+    /// Refills that found the pool empty before reaching the high watermark
+    pub exhausted: u32,
+    /// Descriptors those refills left without a buffer, summed
+    pub shortfall: u32,
+}
+
+impl Default for PoolStats {
+    fn default() -> Self {
+        Self { node: NODE_ANY, low: 0, high: 0, exhausted: 0, shortfall: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolLayout {
+    pub buffer_count: u32,
+    pub buffer_size: u32,
+}
+
+impl PoolLayout {
+    pub fn new(buffer_count: u32, buffer_size: u32) -> Result<Self, PoolError> {
+        if !buffer_count.is_power_of_two()
+            || buffer_count > MAX_BUFFERS
+            || buffer_size == 0
+            || buffer_size > MAX_BUFFER_SIZE
+            || !(buffer_size as usize).is_multiple_of(BUFFER_ALIGN)
+        {
+            return Err(PoolError::InvalidLayout);
+        }
+        Ok(Self { buffer_count, buffer_size })
+    }
+
+    /// A pool of page-sized buffers, each one page of its own
+    pub fn page_sized(buffer_count: u32) -> Result<Self, PoolError> {
+        Self::new(buffer_count, PAGE_BUFFER_SIZE)
+    }
+
+    fn buffer_align(&self) -> usize {
+        if self.buffer_size != 0 && (self.buffer_size as usize).is_multiple_of(PAGE_SIZE) {
+            PAGE_SIZE
+        } else {
+            BUFFER_ALIGN
+        }
+    }
+
+    pub fn fill_offset(&self) -> usize {
+        HEADER_SIZE
+    }
+
+    pub fn rx_offset(&self) -> usize {
+        self.fill_offset() + self.buffer_count as usize * FILL_ENTRY_SIZE
+    }
+
+    pub fn buffers_offset(&self) -> usize {
+        let end = self.rx_offset() + self.buffer_count as usize * RX_ENTRY_SIZE;
+        end.next_multiple_of(self.buffer_align())
+    }
+
+    pub fn region_size(&self) -> usize {
+        self.buffers_offset() + self.buffer_count as usize * self.buffer_size as usize
+    }
+
+    fn buffer_offset(&self, buffer: u32) -> usize {
+        self.buffers_offset() + buffer as usize * self.buffer_size as usize
+    }
+
+    /// Whether `completion` names bytes inside one buffer of the pool
+    fn contains(&self, completion: &RxCompletion) -> bool {
+        completion.buffer < self.buffer_count && completion.offset as u32 + completion.length as u32 <= self.buffer_size
+    }
+}
+
+// Mapped pool region shared by both ends
+struct Region {
+    base: NonNull<u8>,
+    layout: PoolLayout,
+}
+
+impl Region {
+    unsafe fn new(base: *mut u8, size: usize, layout: PoolLayout) -> Result<Self, PoolError> {
+        let base = NonNull::new(base).ok_or(PoolError::BadRegion)?;
+        if !(base.as_ptr() as usize).is_multiple_of(8) || size < layout.region_size().max(HEADER_SIZE) {
+            return Err(PoolError::BadRegion);
+        }
+        Ok(Self { base, layout })
+    }
+
+    fn field(&self, offset: usize) -> &AtomicU32 {
+        // Header fields and fill entries are 4-byte aligned inside a region
+        // checked to be 8-byte aligned
+        unsafe { &*(self.base.as_ptr().add(offset) as *const AtomicU32) }
+    }
+
+    fn read(&self, offset: usize, out: &mut [u8]) {
+        unsafe { ptr::copy_nonoverlapping(self.base.as_ptr().add(offset), out.as_mut_ptr(), out.len()) }
+    }
+
+    fn write(&self, offset: usize, data: &[u8]) {
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), self.base.as_ptr().add(offset), data.len()) }
+    }
+
+    fn fill_entry(&self, index: u32) -> &AtomicU32 {
+        let slot = (index & (self.layout.buffer_count - 1)) as usize;
+        self.field(self.layout.fill_offset() + slot * FILL_ENTRY_SIZE)
+    }
+
+    fn rx_entry_offset(&self, index: u32) -> usize {
+        let slot = (index & (self.layout.buffer_count - 1)) as usize;
+        self.layout.rx_offset() + slot * RX_ENTRY_SIZE
+    }
+
+    fn buffer(&self, buffer: u32) -> *mut u8 {
+        unsafe { self.base.as_ptr().add(self.layout.buffer_offset(buffer)) }
+    }
+}
+
+/// Driver end: takes free buffers for the device and delivers filled ones
+pub struct DriverPool {
+    region: Region,
+    fill_head: u32,
+    rx_tail: u32,
+}
+
+impl DriverPool {
+    /// Lay out a fresh pool, every buffer free for the driver
+    ///
+    /// # Safety
+    /// `base` must be valid for reads and writes of `size` bytes for as
+    /// long as the pool is used, and not be used for anything else.
+    pub unsafe fn init(base: *mut u8, size: usize, layout: PoolLayout) -> Result<Self, PoolError> {
+        let region = Region::new(base, size, layout)?;
+        region.write(0, &[0u8; HEADER_SIZE]);
+        for buffer in 0..layout.buffer_count {
+            region.fill_entry(buffer).store(buffer, Ordering::Relaxed);
+        }
+        region.field(BUFFER_COUNT).store(layout.buffer_count, Ordering::Relaxed);
+        region.field(BUFFER_SIZE).store(layout.buffer_size, Ordering::Relaxed);
+        region.field(FILL_TAIL).store(layout.buffer_count, Ordering::Relaxed);
+        region.field(NODE).store(NODE_ANY, Ordering::Relaxed);
+        region.field(VERSION).store(POOL_VERSION, Ordering::Relaxed);
+        region.field(MAGIC).store(POOL_MAGIC, Ordering::Release);
+        Ok(Self { region, fill_head: 0, rx_tail: 0 })
+    }
+
+    pub fn layout(&self) -> PoolLayout {
+        self.region.layout
+    }
+
+    /// Next free buffer, now owned by the driver
+    pub fn take(&mut self) -> Result<Option<u32>, PoolError> {
+        let layout = self.region.layout;
+        let tail = self.region.field(FILL_TAIL).load(Ordering::Acquire);
+        let queued = tail.wrapping_sub(self.fill_head);
+        if queued > layout.buffer_count {
+            return Err(PoolError::Corrupt);
+        }
+        if queued == 0 {
+            return Ok(None);
+        }
+        let buffer = self.region.fill_entry(self.fill_head).load(Ordering::Relaxed);
+        if buffer >= layout.buffer_count {
+            return Err(PoolError::Corrupt);
+        }
+        self.fill_head = self.fill_head.wrapping_add(1);
+        self.region.field(FILL_HEAD).store(self.fill_head, Ordering::Release);
+        Ok(Some(buffer))
+    }
+
+    /// Free buffers waiting on the fill ring
+    pub fn available(&self) -> u32 {
+        self.region.field(FILL_TAIL).load(Ordering::Acquire).wrapping_sub(self.fill_head)
+    }
+
+    /// Address of a buffer, for the device descriptors pointing at it
+    pub fn buffer_ptr(&self, buffer: u32) -> Result<*mut u8, PoolError> {
+        if buffer >= self.region.layout.buffer_count {
+            return Err(PoolError::InvalidBuffer);
+        }
+        Ok(self.region.buffer(buffer))
+    }
+
+    /// Hand a filled buffer to the server. The driver must not touch the
+    /// buffer again until it comes back through take().
+    pub fn deliver(&mut self, completion: &RxCompletion) -> Result<(), PoolError> {
+        let layout = self.region.layout;
+        if !layout.contains(completion) {
+            return Err(PoolError::InvalidBuffer);
+        }
+        let head = self.region.field(RX_HEAD).load(Ordering::Acquire);
+        let used = self.rx_tail.wrapping_sub(head);
+        if used > layout.buffer_count {
+            return Err(PoolError::Corrupt);
+        }
+        if used == layout.buffer_count {
+            return Err(PoolError::Full);
+        }
+        self.region.write(self.region.rx_entry_offset(self.rx_tail), &completion.encode());
+        self.rx_tail = self.rx_tail.wrapping_add(1);
+        self.region.field(RX_TAIL).store(self.rx_tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Buffers delivered but not yet picked up by the server
+    pub fn pending(&self) -> u32 {
+        self.rx_tail.wrapping_sub(self.region.field(RX_HEAD).load(Ordering::Acquire))
+    }
+
+    /// Publish the pool statistics for the server to report
+    pub fn publish(&self, stats: &PoolStats) {
+        for (field, value) in [
+            (NODE, stats.node),
+            (LOW_WATERMARK, stats.low),
+            (HIGH_WATERMARK, stats.high),
+            (EXHAUSTED, stats.exhausted),
+            (SHORTFALL, stats.shortfall),
+        ] {
+            self.region.field(field).store(value, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Server end: picks up filled buffers and recycles them
+pub struct ServerPool {
+    region: Region,
+    fill_tail: u32,
+    rx_head: u32,
+    held: Vec<bool>,
+}
+
+impl ServerPool {
+    /// Attach to a pool laid out by the driver
+    ///
+    /// # Safety
+    /// `base` must be valid for reads and writes of `size` bytes for as
+    /// long as the pool is used. The driver may write to the region at
+    /// any time; nothing read from it is trusted.
+    pub unsafe fn attach(base: *mut u8, size: usize) -> Result<Self, PoolError> {
+        let header = Region::new(base, size, PoolLayout { buffer_count: 0, buffer_size: 0 })?;
+        if header.field(MAGIC).load(Ordering::Acquire) != POOL_MAGIC
+            || header.field(VERSION).load(Ordering::Relaxed) != POOL_VERSION
+        {
+            return Err(PoolError::BadMagic);
+        }
+        let layout = PoolLayout::new(
+            header.field(BUFFER_COUNT).load(Ordering::Relaxed),
+            header.field(BUFFER_SIZE).load(Ordering::Relaxed),
+        )?;
+        let region = Region::new(base, size, layout)?;
+        let fill_tail = region.field(FILL_TAIL).load(Ordering::Relaxed);
+        let rx_head = region.field(RX_HEAD).load(Ordering::Relaxed);
+        Ok(Self { region, fill_tail, rx_head, held: vec![false; layout.buffer_count as usize] })
+    }
+
+    pub fn layout(&self) -> PoolLayout {
+        self.region.layout
+    }
+
+    /// Next filled buffer, now held by the server until recycled. A
+    /// completion naming bytes outside its buffer is refused and the
+    /// buffer returned to the driver.
+    pub fn next_received(&mut self) -> Result<Option<RxCompletion>, PoolError> {
+        let layout = self.region.layout;
+        let tail = self.region.field(RX_TAIL).load(Ordering::Acquire);
+        let queued = tail.wrapping_sub(self.rx_head);
+        if queued > layout.buffer_count {
+            return Err(PoolError::Corrupt);
+        }
+        if queued == 0 {
+            return Ok(None);
+        }
+
+        let mut bytes = [0u8; RX_ENTRY_SIZE];
+        self.region.read(self.region.rx_entry_offset(self.rx_head), &mut bytes);
+        self.rx_head = self.rx_head.wrapping_add(1);
+        self.region.field(RX_HEAD).store(self.rx_head, Ordering::Release);
+
+        let completion = RxCompletion::decode(&bytes);
+        let held = self.held.get_mut(completion.buffer as usize).ok_or(PoolError::InvalidBuffer)?;
+        if *held {
+            return Err(PoolError::Corrupt);
+        }
+        *held = true;
+        if !layout.contains(&completion) {
+            self.recycle(completion.buffer)?;
+            return Err(PoolError::InvalidBuffer);
+        }
+        Ok(Some(completion))
+    }
+
+    /// The frame of a held buffer, where the device wrote it
+    pub fn packet(&self, completion: &RxCompletion) -> Result<&[u8], PoolError> {
+        if !self.region.layout.contains(completion) || !self.held[completion.buffer as usize] {
+            return Err(PoolError::InvalidBuffer);
+        }
+        let start = unsafe { self.region.buffer(completion.buffer).add(completion.offset as usize) };
+        Ok(unsafe { core::slice::from_raw_parts(start, completion.length as usize) })
+    }
+
+    /// Give a held buffer back to the driver
+    pub fn recycle(&mut self, buffer: u32) -> Result<(), PoolError> {
+        let layout = self.region.layout;
+        match self.held.get(buffer as usize) {
+            Some(true) => {}
+            _ => return Err(PoolError::InvalidBuffer),
+        }
+        let head = self.region.field(FILL_HEAD).load(Ordering::Acquire);
+        let used = self.fill_tail.wrapping_sub(head);
+        if used > layout.buffer_count {
+            return Err(PoolError::Corrupt);
+        }
+        if used == layout.buffer_count {
+            return Err(PoolError::Full);
+        }
+        self.region.fill_entry(self.fill_tail).store(buffer, Ordering::Relaxed);
+        self.fill_tail = self.fill_tail.wrapping_add(1);
+        self.region.field(FILL_TAIL).store(self.fill_tail, Ordering::Release);
+        self.held[buffer as usize] = false;
+        Ok(())
+    }
+
+    /// Statistics last published by the driver, as it wrote them
+    pub fn stats(&self) -> PoolStats {
+        let field = |offset| self.region.field(offset).load(Ordering::Relaxed);
+        PoolStats {
+            node: field(NODE),
+            low: field(LOW_WATERMARK),
+            high: field(HIGH_WATERMARK),
+            exhausted: field(EXHAUSTED),
+            shortfall: field(SHORTFALL),
+        }
+    }
+
+    /// Buffers currently held by the server
+    pub fn held(&self) -> u32 {
+        self.held.iter().filter(|&&held| held).count() as u32
+    }
+
+    /// Run up to `max` received frames (0 for all) through `handle` in
+    /// place and recycle their buffers; returns the number handled.
+    /// Buffers returned unused are recycled without calling `handle`.
+    pub fn process<F>(&mut self, max: u32, mut handle: F) -> Result<u32, PoolError>
+    where
+        F: FnMut(&RxCompletion, &[u8]),
+    {
+        let mut handled = 0;
+        while max == 0 || handled < max {
+            let completion = match self.next_received()? {
+                Some(completion) => completion,
+                None => break,
+            };
+            if completion.length != 0 {
+                handle(&completion, self.packet(&completion)?);
+                handled += 1;
+            }
+            self.recycle(completion.buffer)?;
+        }
+        Ok(handled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(layout: PoolLayout) -> Vec<u64> {
+        vec![0u64; layout.region_size().div_ceil(8)]
+    }
+
+    #[test]
+    fn test_layout_is_validated() {
+        assert_eq!(PoolLayout::new(3, 2048), Err(PoolError::InvalidLayout));
+        assert_eq!(PoolLayout::new(0, 2048), Err(PoolError::InvalidLayout));
+        assert_eq!(PoolLayout::new(2 * MAX_BUFFERS, 2048), Err(PoolError::InvalidLayout));
+        assert_eq!(PoolLayout::new(8, 1000), Err(PoolError::InvalidLayout));
+        assert_eq!(PoolLayout::new(8, 2 * MAX_BUFFER_SIZE), Err(PoolError::InvalidLayout));
+
+        let layout = PoolLayout::new(8, 2048).unwrap();
+        assert_eq!(layout.rx_offset(), HEADER_SIZE + 8 * FILL_ENTRY_SIZE);
+        assert_eq!(layout.buffers_offset() % 64, 0);
+        assert_eq!(layout.region_size(), layout.buffers_offset() + 8 * 2048);
+        // Page-sized buffers start on a page boundary
+        let pages = PoolLayout::page_sized(8).unwrap();
+        assert_eq!((pages.buffers_offset(), pages.region_size()), (4096, 9 * 4096));
+
+        let mut memory = region(layout);
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        assert!(matches!(unsafe { ServerPool::attach(base, size) }, Err(PoolError::BadMagic)));
+        assert!(matches!(unsafe { DriverPool::init(base, size - 64, layout) }, Err(PoolError::BadRegion)));
+        assert!(matches!(
+            unsafe { DriverPool::init(base.wrapping_add(4), size - 4, layout) },
+            Err(PoolError::BadRegion)
+        ));
+    }
+
+    #[test]
+    fn test_frames_are_handed_over_in_place() {
+        let layout = PoolLayout::new(4, 256).unwrap();
+        let mut memory = region(layout);
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        let mut driver = unsafe { DriverPool::init(base, size, layout) }.unwrap();
+        let mut server = unsafe { ServerPool::attach(base, size) }.unwrap();
+
+        // The device writes a header and a frame into every free buffer
+        assert_eq!(driver.available(), 4);
+        let mut taken = Vec::new();
+        while let Some(buffer) = driver.take().unwrap() {
+            let frame = unsafe { driver.buffer_ptr(buffer).unwrap().add(12) };
+            unsafe { frame.write_bytes(buffer as u8 + 1, 60) };
+            taken.push(buffer);
+        }
+        assert_eq!(taken, [0, 1, 2, 3]);
+        for &buffer in &taken {
+            let completion = RxCompletion { buffer, offset: 12, length: 60, queue: 1, flags: FLAG_CHECKSUM_VALID };
+            driver.deliver(&completion).unwrap();
+        }
+        assert_eq!(driver.pending(), 4);
+
+        // The server reads the frames where the device wrote them
+        let completion = server.next_received().unwrap().unwrap();
+        let frame = server.packet(&completion).unwrap();
+        assert_eq!(frame.as_ptr(), unsafe { driver.buffer_ptr(0).unwrap().add(12) } as *const u8);
+        assert_eq!((frame.len(), frame[0], completion.queue), (60, 1, 1));
+        assert_eq!(server.held(), 1);
+        server.recycle(completion.buffer).unwrap();
+        assert_eq!(server.recycle(completion.buffer), Err(PoolError::InvalidBuffer));
+
+        let mut seen = Vec::new();
+        assert_eq!(server.process(0, |completion, frame| seen.push((completion.buffer, frame[59]))).unwrap(), 3);
+        assert_eq!(seen, [(1, 2), (2, 3), (3, 4)]);
+        assert_eq!((server.held(), driver.pending(), driver.available()), (0, 0, 4));
+
+        // A buffer the device failed to fill comes back unused
+        let buffer = driver.take().unwrap().unwrap();
+        driver.deliver(&RxCompletion { buffer, ..RxCompletion::default() }).unwrap();
+        assert_eq!(server.process(0, |_, _| unreachable!()).unwrap(), 0);
+        assert_eq!(driver.available(), 4);
+
+        // Indices keep working after wrapping around the rings
+        for round in 1..=10u16 {
+            let buffer = driver.take().unwrap().unwrap();
+            driver.deliver(&RxCompletion { buffer, length: round, ..RxCompletion::default() }).unwrap();
+            assert_eq!(server.process(1, |completion, _| assert_eq!(completion.length, round)).unwrap(), 1);
+        }
+        assert_eq!(driver.available(), 4);
+
+        // Statistics go through the header
+        assert_eq!(server.stats(), PoolStats::default());
+        let stats = PoolStats { node: 1, low: 3, high: 4, exhausted: 2, shortfall: 5 };
+        driver.publish(&stats);
+        assert_eq!(server.stats(), stats);
+    }
+
+    #[test]
+    fn test_misbehaving_drivers_are_caught() {
+        let layout = PoolLayout::new(2, 128).unwrap();
+        let mut memory = region(layout);
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        let mut driver = unsafe { DriverPool::init(base, size, layout) }.unwrap();
+        let mut server = unsafe { ServerPool::attach(base, size) }.unwrap();
+
+        assert_eq!(
+            driver.deliver(&RxCompletion { buffer: 2, ..RxCompletion::default() }),
+            Err(PoolError::InvalidBuffer)
+        );
+        assert_eq!(
+            driver.deliver(&RxCompletion { buffer: 0, offset: 100, length: 29, ..RxCompletion::default() }),
+            Err(PoolError::InvalidBuffer)
+        );
+
+        // The same buffer delivered twice
+        let buffer = driver.take().unwrap().unwrap();
+        driver.deliver(&RxCompletion { buffer, length: 10, ..RxCompletion::default() }).unwrap();
+        driver.deliver(&RxCompletion { buffer, length: 10, ..RxCompletion::default() }).unwrap();
+        assert!(server.next_received().unwrap().is_some());
+        assert_eq!(server.next_received(), Err(PoolError::Corrupt));
+        server.recycle(buffer).unwrap();
+
+        // A completion reaching past its buffer is refused, the buffer
+        // returned; rx index 2 lands in slot 0
+        let buffer = driver.take().unwrap().unwrap();
+        let entry = RxCompletion { buffer, offset: 0, length: 4000, ..RxCompletion::default() };
+        unsafe {
+            base.add(layout.rx_offset()).copy_from(entry.encode().as_ptr(), RX_ENTRY_SIZE);
+            (base.add(RX_TAIL) as *mut u32).write(3);
+        }
+        assert_eq!(server.next_received(), Err(PoolError::InvalidBuffer));
+        assert_eq!((server.held(), driver.available()), (0, 2));
+
+        // A driver moving its tail past the ring is caught
+        unsafe { (base.add(RX_TAIL) as *mut u32).write(1000) };
+        assert_eq!(server.next_received(), Err(PoolError::Corrupt));
+    }
+}
@@ -0,0 +1,233 @@
+/*
+ * Orion Operating System - Receive Pool Protocol
+ *
+ * IPC requests a NIC driver sends the network server to share a receive
+ * pool. Opcodes are kept clear of the socket, interface and ring ranges
+ * the server already accepts. Same little-endian framing as every
+ * server: a 32-bit opcode first, replies start with a 32-bit signed
+ * status.
+ *
+ *   REGISTER    memory:u64  -> pool:u32
+ *   DELIVER     pool:u32    -> handled:u32 flags:u32
+ *   UNREGISTER  pool:u32    -> (empty)
+ *   STATS       (none)      -> count:u32 {pool:u32 node:u32
+ *                              buffer_count:u32 buffer_size:u32 queued:u32
+ *                              low:u32 high:u32 exhausted:u32
+ *                              shortfall:u32}*
+ *
+ * REGISTER names the shared memory object holding a pool the driver laid
+ * out; the server maps it and checks the header. DELIVER is the doorbell:
+ * the server runs every frame queued on the rx ring through the stack in
+ * place and has put the buffers back on the fill ring by the time it
+ * replies. While socket memory is under pressure the server leaves frames
+ * queued and sets DELIVER_BACKPRESSURE: the driver then stops reposting
+ * buffers and keeps ringing until a reply comes back without the flag.
+ * Pools belong to the process that registered them. STATS lists every
+ * pool with the statistics its driver published (see refill.rs) and the
+ * frames left queued on its rx ring, for any caller.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::pool::{PoolLayout, PoolStats};
+
+// Opcodes
+pub const OP_POOL_REGISTER: u32 = 0x110;
+pub const OP_POOL_DELIVER: u32 = 0x111;
+pub const OP_POOL_UNREGISTER: u32 = 0x112;
+pub const OP_POOL_STATS: u32 = 0x113;
+
+/// Pools one process may register, one per receive queue
+pub const MAX_POOLS_PER_PROCESS: usize = 8;
+
+/// DELIVER reply flag: frames were left queued, stop reposting buffers
+pub const DELIVER_BACKPRESSURE: u32 = 1 << 0;
+
+pub const POOL_RECORD_SIZE: usize = 36;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxPoolRequest {
+    Register { memory: u64 },
+    Deliver { pool: u32 },
+    Unregister { pool: u32 },
+    Stats,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(read_u32(data, offset)? as u64 | (read_u32(data, offset + 4)? as u64) << 32)
+}
+
+impl RxPoolRequest {
+    /// Decode a pool request; None for anything else, so servers can fall
+    /// back to their own protocol
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_POOL_REGISTER => Some(RxPoolRequest::Register { memory: read_u64(data, 4)? }),
+            OP_POOL_DELIVER => Some(RxPoolRequest::Deliver { pool: read_u32(data, 4)? }),
+            OP_POOL_UNREGISTER => Some(RxPoolRequest::Unregister { pool: read_u32(data, 4)? }),
+            OP_POOL_STATS => Some(RxPoolRequest::Stats),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12);
+        match *self {
+            RxPoolRequest::Register { memory } => {
+                out.extend_from_slice(&OP_POOL_REGISTER.to_le_bytes());
+                out.extend_from_slice(&memory.to_le_bytes());
+            }
+            RxPoolRequest::Deliver { pool } => {
+                out.extend_from_slice(&OP_POOL_DELIVER.to_le_bytes());
+                out.extend_from_slice(&pool.to_le_bytes());
+            }
+            RxPoolRequest::Unregister { pool } => {
+                out.extend_from_slice(&OP_POOL_UNREGISTER.to_le_bytes());
+                out.extend_from_slice(&pool.to_le_bytes());
+            }
+            RxPoolRequest::Stats => out.extend_from_slice(&OP_POOL_STATS.to_le_bytes()),
+        }
+        out
+    }
+}
+
+/// Successful DELIVER reply
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliverReply {
+    pub handled: u32,
+    pub backpressure: bool,
+}
+
+impl DeliverReply {
+    /// Decode a reply, status included; None for a failed or short reply
+    pub fn decode(reply: &[u8]) -> Option<Self> {
+        if read_u32(reply, 0)? != 0 {
+            return None;
+        }
+        let flags = read_u32(reply, 8)?;
+        Some(Self { handled: read_u32(reply, 4)?, backpressure: flags & DELIVER_BACKPRESSURE != 0 })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12);
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(&self.handled.to_le_bytes());
+        out.extend_from_slice(&(if self.backpressure { DELIVER_BACKPRESSURE } else { 0 }).to_le_bytes());
+        out
+    }
+}
+
+/// One pool of a STATS reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolRecord {
+    pub pool: u32,
+    pub layout: PoolLayout,
+    /// Frames waiting on the rx ring, left there under backpressure
+    pub queued: u32,
+    pub stats: PoolStats,
+}
+
+impl PoolRecord {
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        for value in [
+            self.pool,
+            self.stats.node,
+            self.layout.buffer_count,
+            self.layout.buffer_size,
+            self.queued,
+            self.stats.low,
+            self.stats.high,
+            self.stats.exhausted,
+            self.stats.shortfall,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let field = |index: usize| read_u32(data, index * 4);
+        Some(Self {
+            pool: field(0)?,
+            layout: PoolLayout { buffer_count: field(2)?, buffer_size: field(3)? },
+            queued: field(4)?,
+            stats: PoolStats {
+                node: field(1)?,
+                low: field(5)?,
+                high: field(6)?,
+                exhausted: field(7)?,
+                shortfall: field(8)?,
+            },
+        })
+    }
+
+    /// Decode a STATS reply, status included; None for a failed or short
+    /// reply
+    pub fn decode_reply(reply: &[u8]) -> Option<Vec<Self>> {
+        if read_u32(reply, 0)? != 0 {
+            return None;
+        }
+        let count = read_u32(reply, 4)? as usize;
+        let records = reply.get(8..)?;
+        if records.len() < count.checked_mul(POOL_RECORD_SIZE)? {
+            return None;
+        }
+        records.chunks_exact(POOL_RECORD_SIZE).take(count).map(Self::decode).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_round_trip() {
+        for request in [
+            RxPoolRequest::Register { memory: 0x1234_5678_9abc },
+            RxPoolRequest::Deliver { pool: 3 },
+            RxPoolRequest::Unregister { pool: 3 },
+            RxPoolRequest::Stats,
+        ] {
+            assert_eq!(RxPoolRequest::decode(&request.encode()), Some(request));
+        }
+        let register = RxPoolRequest::Register { memory: 1 }.encode();
+        assert_eq!(RxPoolRequest::decode(&register[..8]), None);
+        assert_eq!(RxPoolRequest::decode(&0x101u32.to_le_bytes()), None);
+    }
+
+    #[test]
+    fn test_deliver_replies() {
+        let reply = DeliverReply { handled: 12, backpressure: true };
+        assert_eq!(DeliverReply::decode(&reply.encode()), Some(reply));
+        assert_eq!(DeliverReply::decode(&reply.encode()[..8]), None);
+        assert_eq!(DeliverReply::decode(&(-16i32).to_le_bytes()), None);
+    }
+
+    #[test]
+    fn test_stats_replies() {
+        let record = PoolRecord {
+            pool: 2,
+            layout: PoolLayout { buffer_count: 256, buffer_size: 4096 },
+            queued: 3,
+            stats: PoolStats { node: 1, low: 192, high: 256, exhausted: 4, shortfall: 70 },
+        };
+        let mut reply = 0i32.to_le_bytes().to_vec();
+        reply.extend_from_slice(&2u32.to_le_bytes());
+        record.encode(&mut reply);
+        record.encode(&mut reply);
+        assert_eq!(reply.len(), 8 + 2 * POOL_RECORD_SIZE);
+        assert_eq!(PoolRecord::decode_reply(&reply), Some(alloc::vec![record, record]));
+        assert_eq!(PoolRecord::decode_reply(&reply[..8 + POOL_RECORD_SIZE]), None);
+        assert_eq!(PoolRecord::decode_reply(&(-22i32).to_le_bytes()), None);
+    }
+}
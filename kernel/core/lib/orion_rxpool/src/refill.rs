@@ -0,0 +1,253 @@
+/*
+ * Orion Operating System - Receive Pool Refill
+ *
+ * Driver side of a pool of page-sized buffers placed on one NUMA node,
+ * shared by the NIC drivers so none allocates or frees per packet: a
+ * buffer goes from the fill ring to a device descriptor, back to the
+ * server with its frame and, once the stack is done with it, onto the
+ * fill ring again. A driver with queues on several nodes keeps one pool
+ * per queue, on the queue's node, and buffers only ever come back to the
+ * pool they were taken from.
+ *
+ * Descriptors are refilled by watermark. Nothing is posted while the
+ * device holds at least `low` buffers; below that, buffers are posted up
+ * to `high` in one go, so the doorbell rings once per batch rather than
+ * once per frame. A refill that runs the pool dry first counts as an
+ * exhaustion, and the descriptors it left empty as shortfall: a pool
+ * exhausted often is too small for the rate, or the stack is slow to
+ * hand buffers back. Both are published in the pool header, where the
+ * network server reports them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::pool::{DriverPool, PoolError, PoolLayout, PoolStats, RxCompletion, NODE_ANY};
+
+/// shm_create() flag placing the pages of the object on the NUMA node
+/// given in bits 16-23
+pub const SHM_NODE: u32 = 0x100;
+const SHM_NODE_SHIFT: u32 = 16;
+
+/// shm_create() flags for a pool on `node`, anywhere when None
+pub fn shm_flags(node: Option<u32>) -> u32 {
+    match node {
+        Some(node) if node <= 0xFF => SHM_NODE | node << SHM_NODE_SHIFT,
+        _ => 0,
+    }
+}
+
+/// Buffers posted to the device below which a refill starts, and up to
+/// which it posts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watermarks {
+    pub low: u32,
+    pub high: u32,
+}
+
+impl Watermarks {
+    pub fn new(low: u32, high: u32) -> Result<Self, PoolError> {
+        if low == 0 || low > high {
+            return Err(PoolError::InvalidLayout);
+        }
+        Ok(Self { low, high })
+    }
+
+    /// For a ring of `descriptors`: refill once a quarter of it has been
+    /// used, back to full
+    pub fn for_ring(descriptors: u32) -> Self {
+        let high = descriptors.max(1);
+        Self { low: (high - high / 4).max(1), high }
+    }
+}
+
+/// A pool and the buffers of it the device holds
+pub struct NodePool {
+    pool: DriverPool,
+    watermarks: Watermarks,
+    posted: u32,
+    /// Taken but refused by a full device ring, posted first next time
+    spare: Option<u32>,
+    stats: PoolStats,
+}
+
+impl NodePool {
+    /// Lay out a fresh pool in memory allocated on `node`
+    ///
+    /// # Safety
+    /// As DriverPool::init.
+    pub unsafe fn init(
+        base: *mut u8,
+        size: usize,
+        layout: PoolLayout,
+        node: Option<u32>,
+        watermarks: Watermarks,
+    ) -> Result<Self, PoolError> {
+        if watermarks.high > layout.buffer_count {
+            return Err(PoolError::InvalidLayout);
+        }
+        let pool = DriverPool::init(base, size, layout)?;
+        let stats = PoolStats {
+            node: node.unwrap_or(NODE_ANY),
+            low: watermarks.low,
+            high: watermarks.high,
+            ..PoolStats::default()
+        };
+        pool.publish(&stats);
+        Ok(Self { pool, watermarks, posted: 0, spare: None, stats })
+    }
+
+    pub fn pool(&self) -> &DriverPool {
+        &self.pool
+    }
+
+    pub fn node(&self) -> Option<u32> {
+        Some(self.stats.node).filter(|&node| node != NODE_ANY)
+    }
+
+    pub fn watermarks(&self) -> Watermarks {
+        self.watermarks
+    }
+
+    /// Buffers posted to the device
+    pub fn posted(&self) -> u32 {
+        self.posted
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    /// Address of a buffer, for the descriptor pointing at it
+    pub fn buffer_ptr(&self, buffer: u32) -> Result<*mut u8, PoolError> {
+        self.pool.buffer_ptr(buffer)
+    }
+
+    /// Once fewer than `low` buffers are posted, post free ones up to
+    /// `high` through `post`, which returns false when the device ring is
+    /// full. Returns the number posted.
+    pub fn refill<F>(&mut self, mut post: F) -> Result<u32, PoolError>
+    where
+        F: FnMut(u32, *mut u8) -> bool,
+    {
+        if self.posted >= self.watermarks.low {
+            return Ok(0);
+        }
+        let mut count = 0;
+        while self.posted < self.watermarks.high {
+            let buffer = match self.spare.take() {
+                Some(buffer) => buffer,
+                None => match self.pool.take()? {
+                    Some(buffer) => buffer,
+                    None => {
+                        self.exhausted(self.watermarks.high - self.posted);
+                        break;
+                    }
+                },
+            };
+            if !post(buffer, self.pool.buffer_ptr(buffer)?) {
+                self.spare = Some(buffer);
+                break;
+            }
+            self.posted += 1;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// A posted buffer came back from the device: hand it to the server,
+    /// with its frame or unused
+    pub fn complete(&mut self, completion: &RxCompletion) -> Result<(), PoolError> {
+        self.posted = self.posted.saturating_sub(1);
+        self.pool.deliver(completion)
+    }
+
+    fn exhausted(&mut self, shortfall: u32) {
+        self.stats.exhausted = self.stats.exhausted.wrapping_add(1);
+        self.stats.shortfall = self.stats.shortfall.wrapping_add(shortfall);
+        self.pool.publish(&self.stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::ServerPool;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_refills_between_watermarks() {
+        assert_eq!(Watermarks::for_ring(256), Watermarks { low: 192, high: 256 });
+        assert_eq!(Watermarks::new(5, 4), Err(PoolError::InvalidLayout));
+        assert_eq!(shm_flags(Some(2)), SHM_NODE | 2 << 16);
+        assert_eq!(shm_flags(None), 0);
+
+        let layout = PoolLayout::page_sized(8).unwrap();
+        // A page aligned region, as shm_attach() maps it
+        let mut memory = vec![0u64; (layout.region_size() + 4096) / 8];
+        let size = layout.region_size();
+        let base = memory.as_mut_ptr() as *mut u8;
+        let base = base.wrapping_add(base.align_offset(4096));
+        let watermarks = Watermarks::new(4, 6).unwrap();
+        assert!(matches!(
+            unsafe { NodePool::init(base, size, layout, Some(1), Watermarks::new(4, 16).unwrap()) },
+            Err(PoolError::InvalidLayout)
+        ));
+        let mut pool = unsafe { NodePool::init(base, size, layout, Some(1), watermarks) }.unwrap();
+        let mut server = unsafe { ServerPool::attach(base, size) }.unwrap();
+        assert_eq!((server.stats().node, server.stats().low, server.stats().high), (1, 4, 6));
+
+        // Filled up to the high watermark, every buffer its own page
+        let mut ring = Vec::new();
+        assert_eq!(
+            pool.refill(|buffer, address| {
+                assert_eq!(address as usize % 4096, 0);
+                ring.push(buffer);
+                true
+            })
+            .unwrap(),
+            6
+        );
+        assert_eq!(pool.posted(), 6);
+
+        // Nothing posted while at least `low` buffers are
+        for buffer in ring.drain(..2) {
+            pool.complete(&RxCompletion { buffer, length: 60, ..RxCompletion::default() }).unwrap();
+        }
+        assert_eq!(pool.refill(|_, _| unreachable!()).unwrap(), 0);
+
+        // The server still holds the two delivered: two left free, four short
+        pool.complete(&RxCompletion { buffer: ring.remove(0), length: 60, ..RxCompletion::default() }).unwrap();
+        assert_eq!(
+            pool.refill(|buffer, _| {
+                ring.push(buffer);
+                true
+            })
+            .unwrap(),
+            2
+        );
+        assert_eq!((pool.posted(), pool.stats().exhausted, pool.stats().shortfall), (5, 1, 1));
+        assert_eq!(server.stats(), pool.stats());
+
+        // Once the stack is done the buffers come back; a full device ring
+        // keeps the one it refused for next time
+        assert_eq!(server.process(0, |_, _| {}).unwrap(), 3);
+        for buffer in ring.drain(..2) {
+            pool.complete(&RxCompletion { buffer, ..RxCompletion::default() }).unwrap();
+        }
+        assert_eq!(pool.refill(|_, _| false).unwrap(), 0);
+        assert_eq!(
+            pool.refill(|buffer, _| {
+                ring.push(buffer);
+                true
+            })
+            .unwrap(),
+            3
+        );
+        assert_eq!((pool.posted(), pool.stats().exhausted), (6, 1));
+    }
+}
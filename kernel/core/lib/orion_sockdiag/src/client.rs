@@ -0,0 +1,208 @@
+/*
+ * Orion Operating System - Socket Diagnostics Client
+ *
+ * LIST is paged: each reply carries the entries that fit and a cursor
+ * to continue from, 0 once the walk is over. Sockets opening or closing
+ * during a walk may be skipped or listed twice, as with any netstat.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_http::socket::NetChannel;
+
+use crate::entry::{Protocol, SocketEntry, Summary, TcpState, ENTRY_SIZE};
+
+// Opcodes
+pub const OP_LIST: u32 = 40;
+pub const OP_SUMMARY: u32 = 41;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EIO: i32 = -5;
+
+const FILTER_TCP: u8 = 1 << 0;
+const FILTER_UDP: u8 = 1 << 1;
+
+/// Upper bound on LIST calls in one walk, against a cursor that never ends
+const MAX_PAGES: usize = 4096;
+
+/// Sockets to list; the default lists every socket
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Protocols to list, all when empty
+    pub protocols: Vec<Protocol>,
+    /// TCP states to list, all when empty; a state leaves UDP out
+    pub states: Vec<TcpState>,
+    /// Local or remote port, 0 for any
+    pub port: u16,
+}
+
+impl Filter {
+    fn encode(&self, cursor: u32) -> Vec<u8> {
+        let protocols = self.protocols.iter().fold(0u8, |mask, protocol| {
+            mask | match protocol {
+                Protocol::Tcp => FILTER_TCP,
+                Protocol::Udp => FILTER_UDP,
+            }
+        });
+        let states = self.states.iter().fold(0u32, |mask, state| mask | state.bit());
+
+        let mut args = Vec::with_capacity(16);
+        args.extend_from_slice(&OP_LIST.to_le_bytes());
+        args.push(protocols);
+        args.push(0);
+        args.extend_from_slice(&self.port.to_le_bytes());
+        args.extend_from_slice(&states.to_le_bytes());
+        args.extend_from_slice(&cursor.to_le_bytes());
+        args
+    }
+}
+
+fn status_of(reply: &[u8]) -> Result<&[u8], i32> {
+    if reply.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]]) {
+        STATUS_OK => Ok(&reply[4..]),
+        status => Err(status),
+    }
+}
+
+pub struct SockDiag<C: NetChannel> {
+    channel: C,
+}
+
+impl<C: NetChannel> SockDiag<C> {
+    pub fn new(channel: C) -> Self {
+        Self { channel }
+    }
+
+    /// Every socket matching `filter`
+    pub fn list(&mut self, filter: &Filter) -> Result<Vec<SocketEntry>, i32> {
+        let mut entries = Vec::new();
+        let mut cursor = 0u32;
+        for _ in 0..MAX_PAGES {
+            let reply = self.channel.call(&filter.encode(cursor)).ok_or(STATUS_EIO)?;
+            let payload = status_of(&reply)?;
+            if payload.len() < 8 {
+                return Err(STATUS_EIO);
+            }
+            let next = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+            let count = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]) as usize;
+            let records = &payload[8..];
+            if records.len() < count * ENTRY_SIZE {
+                return Err(STATUS_EIO);
+            }
+            // Entries of unknown protocols or states are skipped
+            entries.extend(
+                records
+                    .chunks_exact(ENTRY_SIZE)
+                    .take(count)
+                    .filter_map(SocketEntry::decode),
+            );
+            if next == 0 {
+                return Ok(entries);
+            }
+            cursor = next;
+        }
+        Err(STATUS_EIO)
+    }
+
+    /// Socket counts and stack-wide counters
+    pub fn summary(&mut self) -> Result<Summary, i32> {
+        let reply = self.channel.call(&OP_SUMMARY.to_le_bytes()).ok_or(STATUS_EIO)?;
+        Summary::decode(status_of(&reply)?).ok_or(STATUS_EIO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+
+    struct Script {
+        replies: VecDeque<Vec<u8>>,
+        requests: Vec<Vec<u8>>,
+    }
+
+    impl NetChannel for &mut Script {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            self.requests.push(request.to_vec());
+            self.replies.pop_front()
+        }
+    }
+
+    fn page(next: u32, ports: &[u16]) -> Vec<u8> {
+        let mut out = STATUS_OK.to_le_bytes().to_vec();
+        out.extend_from_slice(&next.to_le_bytes());
+        out.extend_from_slice(&(ports.len() as u32).to_le_bytes());
+        for port in ports {
+            let mut entry = [0u8; ENTRY_SIZE];
+            entry[0] = 17;
+            entry[12..14].copy_from_slice(&port.to_le_bytes());
+            out.extend_from_slice(&entry);
+        }
+        out
+    }
+
+    #[test]
+    fn test_walks_pages() {
+        let mut script = Script {
+            replies: VecDeque::from(vec![page(7, &[53, 67]), page(0, &[123])]),
+            requests: Vec::new(),
+        };
+        let filter = Filter {
+            protocols: vec![Protocol::Udp],
+            states: Vec::new(),
+            port: 0,
+        };
+        let entries = SockDiag::new(&mut script).list(&filter).unwrap();
+        let ports: Vec<u16> = entries.iter().map(|entry| entry.local_port).collect();
+        assert_eq!(ports, [53, 67, 123]);
+
+        assert_eq!(script.requests[0][4], FILTER_UDP);
+        assert_eq!(&script.requests[0][12..16], &0u32.to_le_bytes());
+        assert_eq!(&script.requests[1][12..16], &7u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_encodes_state_filters() {
+        let filter = Filter {
+            protocols: Vec::new(),
+            states: vec![TcpState::Listen, TcpState::Established],
+            port: 443,
+        };
+        let request = filter.encode(0);
+        assert_eq!(request[4], 0);
+        assert_eq!(&request[6..8], &443u16.to_le_bytes());
+        assert_eq!(&request[8..12], &((1u32 << 1) | (1 << 4)).to_le_bytes());
+    }
+
+    #[test]
+    fn test_reads_the_summary() {
+        let mut reply = STATUS_OK.to_le_bytes().to_vec();
+        reply.extend_from_slice(&[2, 0, 0, 0, 5, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        for counter in 0..17u64 {
+            reply.extend_from_slice(&(counter * 10).to_le_bytes());
+        }
+        let mut script = Script {
+            replies: VecDeque::from(vec![reply, (-22i32).to_le_bytes().to_vec()]),
+            requests: Vec::new(),
+        };
+        let mut diag = SockDiag::new(&mut script);
+        let summary = diag.summary().unwrap();
+        assert_eq!(summary.tcp_sockets, 2);
+        assert_eq!(summary.udp_sockets, 5);
+        assert_eq!(summary.tcp_established, 1);
+        assert_eq!(summary.counters.tcp_retrans_segs, 80);
+        assert_eq!(summary.counters.icmp_out_msgs, 160);
+        assert_eq!(diag.summary(), Err(-22));
+    }
+}
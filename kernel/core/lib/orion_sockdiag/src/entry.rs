@@ -0,0 +1,310 @@
+/*
+ * Orion Operating System - Socket Diagnostics Records
+ *
+ * Decoding of the LIST entries and the SUMMARY reply. Everything is
+ * little-endian; addresses are IPv4 in host order, as the network
+ * server keeps them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+/// Size of one LIST entry
+pub const ENTRY_SIZE: usize = 80;
+/// Counters carried by SUMMARY
+pub const COUNTERS: usize = 17;
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u32_at(bytes, offset) as u64 | (u32_at(bytes, offset + 4) as u64) << 32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        }
+    }
+}
+
+/// TCP states in the order of orion_tcp_state_t
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TcpState {
+    Closed = 0,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+    Close,
+}
+
+const STATES: [TcpState; 12] = [
+    TcpState::Closed,
+    TcpState::Listen,
+    TcpState::SynSent,
+    TcpState::SynReceived,
+    TcpState::Established,
+    TcpState::FinWait1,
+    TcpState::FinWait2,
+    TcpState::CloseWait,
+    TcpState::Closing,
+    TcpState::LastAck,
+    TcpState::TimeWait,
+    TcpState::Close,
+];
+
+impl TcpState {
+    pub fn from_wire(value: u8) -> Option<Self> {
+        STATES.get(value as usize).copied()
+    }
+
+    /// Bit of the state in a LIST state filter
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    /// Column text, as ss prints it
+    pub fn name(self) -> &'static str {
+        match self {
+            TcpState::Closed => "CLOSED",
+            TcpState::Listen => "LISTEN",
+            TcpState::SynSent => "SYN-SENT",
+            TcpState::SynReceived => "SYN-RECV",
+            TcpState::Established => "ESTAB",
+            TcpState::FinWait1 => "FIN-WAIT-1",
+            TcpState::FinWait2 => "FIN-WAIT-2",
+            TcpState::CloseWait => "CLOSE-WAIT",
+            TcpState::Closing => "CLOSING",
+            TcpState::LastAck => "LAST-ACK",
+            TcpState::TimeWait => "TIME-WAIT",
+            TcpState::Close => "CLOSE",
+        }
+    }
+
+    /// State named in a filter ("established", "syn-sent", "listening", ...)
+    pub fn parse(name: &str) -> Option<Self> {
+        let state = match name {
+            "closed" => TcpState::Closed,
+            "listen" | "listening" => TcpState::Listen,
+            "syn-sent" => TcpState::SynSent,
+            "syn-recv" | "syn-received" => TcpState::SynReceived,
+            "established" | "estab" => TcpState::Established,
+            "fin-wait-1" => TcpState::FinWait1,
+            "fin-wait-2" => TcpState::FinWait2,
+            "close-wait" => TcpState::CloseWait,
+            "closing" => TcpState::Closing,
+            "last-ack" => TcpState::LastAck,
+            "time-wait" => TcpState::TimeWait,
+            _ => return None,
+        };
+        Some(state)
+    }
+}
+
+/// One TCP connection or UDP endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketEntry {
+    pub protocol: Protocol,
+    /// None for UDP
+    pub state: Option<TcpState>,
+    /// Congestion control algorithm number (orion_tcp_cc_algorithm_t)
+    pub congestion_control: u8,
+    pub local_ip: u32,
+    pub local_port: u16,
+    /// 0 for listeners and UDP
+    pub remote_ip: u32,
+    pub remote_port: u16,
+    /// Bytes received and not read yet
+    pub recv_queue: u32,
+    /// Bytes queued and not acknowledged yet
+    pub send_queue: u32,
+    /// Segments in flight
+    pub in_flight: u32,
+    pub retransmissions: u32,
+    pub timeouts: u32,
+    /// Windows in segments
+    pub cwnd: u32,
+    pub ssthresh: u32,
+    pub rtt_us: u32,
+    pub rttvar_us: u32,
+    pub rto_ms: u32,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Datagrams dropped on a full queue
+    pub dropped: u64,
+}
+
+impl SocketEntry {
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < ENTRY_SIZE {
+            return None;
+        }
+        let (protocol, state) = match bytes[0] {
+            PROTOCOL_TCP => (Protocol::Tcp, Some(TcpState::from_wire(bytes[1])?)),
+            PROTOCOL_UDP => (Protocol::Udp, None),
+            _ => return None,
+        };
+        Some(Self {
+            protocol,
+            state,
+            congestion_control: bytes[2],
+            local_ip: u32_at(bytes, 4),
+            remote_ip: u32_at(bytes, 8),
+            local_port: u16_at(bytes, 12),
+            remote_port: u16_at(bytes, 14),
+            recv_queue: u32_at(bytes, 16),
+            send_queue: u32_at(bytes, 20),
+            in_flight: u32_at(bytes, 24),
+            retransmissions: u32_at(bytes, 28),
+            timeouts: u32_at(bytes, 32),
+            cwnd: u32_at(bytes, 36),
+            ssthresh: u32_at(bytes, 40),
+            rtt_us: u32_at(bytes, 44),
+            rttvar_us: u32_at(bytes, 48),
+            rto_ms: u32_at(bytes, 52),
+            bytes_sent: u64_at(bytes, 56),
+            bytes_received: u64_at(bytes, 64),
+            dropped: u64_at(bytes, 72),
+        })
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.state == Some(TcpState::Listen)
+    }
+}
+
+/// Stack-wide counters, in the order of orion_tcpip_stats_t
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub ip_in_receives: u64,
+    pub ip_in_hdr_errors: u64,
+    pub ip_in_delivers: u64,
+    pub ip_out_requests: u64,
+    pub ip_out_discards: u64,
+    pub tcp_active_opens: u64,
+    pub tcp_in_segs: u64,
+    pub tcp_out_segs: u64,
+    pub tcp_retrans_segs: u64,
+    pub tcp_timeouts: u64,
+    pub udp_in_datagrams: u64,
+    pub udp_no_ports: u64,
+    pub udp_in_errors: u64,
+    pub udp_out_datagrams: u64,
+    pub icmp_in_msgs: u64,
+    pub icmp_in_errors: u64,
+    pub icmp_out_msgs: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub tcp_sockets: u32,
+    pub udp_sockets: u32,
+    /// TCP connections established or waiting for the local close
+    pub tcp_established: u32,
+    pub counters: Counters,
+}
+
+impl Summary {
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 16 + COUNTERS * 8 {
+            return None;
+        }
+        let counter = |index: usize| u64_at(bytes, 16 + index * 8);
+        Some(Self {
+            tcp_sockets: u32_at(bytes, 0),
+            udp_sockets: u32_at(bytes, 4),
+            tcp_established: u32_at(bytes, 8),
+            counters: Counters {
+                ip_in_receives: counter(0),
+                ip_in_hdr_errors: counter(1),
+                ip_in_delivers: counter(2),
+                ip_out_requests: counter(3),
+                ip_out_discards: counter(4),
+                tcp_active_opens: counter(5),
+                tcp_in_segs: counter(6),
+                tcp_out_segs: counter(7),
+                tcp_retrans_segs: counter(8),
+                tcp_timeouts: counter(9),
+                udp_in_datagrams: counter(10),
+                udp_no_ports: counter(11),
+                udp_in_errors: counter(12),
+                udp_out_datagrams: counter(13),
+                icmp_in_msgs: counter(14),
+                icmp_in_errors: counter(15),
+                icmp_out_msgs: counter(16),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_entries() {
+        let mut bytes = [0u8; ENTRY_SIZE];
+        bytes[0] = PROTOCOL_TCP;
+        bytes[1] = 4;
+        bytes[2] = 2;
+        bytes[4..8].copy_from_slice(&0x0A00_0002u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&0x0A00_0001u32.to_le_bytes());
+        bytes[12..14].copy_from_slice(&22u16.to_le_bytes());
+        bytes[14..16].copy_from_slice(&51000u16.to_le_bytes());
+        bytes[28..32].copy_from_slice(&3u32.to_le_bytes());
+        bytes[36..40].copy_from_slice(&10u32.to_le_bytes());
+        bytes[64..72].copy_from_slice(&(5u64 << 32).to_le_bytes());
+
+        let entry = SocketEntry::decode(&bytes).unwrap();
+        assert_eq!(entry.protocol, Protocol::Tcp);
+        assert_eq!(entry.state, Some(TcpState::Established));
+        assert_eq!(entry.local_port, 22);
+        assert_eq!(entry.remote_ip, 0x0A00_0001);
+        assert_eq!(entry.retransmissions, 3);
+        assert_eq!(entry.cwnd, 10);
+        assert_eq!(entry.bytes_received, 5u64 << 32);
+
+        bytes[1] = 12;
+        assert_eq!(SocketEntry::decode(&bytes), None);
+        bytes[0] = PROTOCOL_UDP;
+        assert_eq!(SocketEntry::decode(&bytes).unwrap().state, None);
+        assert_eq!(SocketEntry::decode(&bytes[..ENTRY_SIZE - 1]), None);
+    }
+
+    #[test]
+    fn test_names_states() {
+        for state in STATES {
+            assert_eq!(TcpState::from_wire(state as u8), Some(state));
+        }
+        assert_eq!(TcpState::parse("established"), Some(TcpState::Established));
+        assert_eq!(TcpState::parse("listening").map(TcpState::bit), Some(1 << 1));
+        assert_eq!(TcpState::parse("bogus"), None);
+        assert_eq!(TcpState::TimeWait.name(), "TIME-WAIT");
+    }
+}
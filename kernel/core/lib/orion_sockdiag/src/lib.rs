@@ -0,0 +1,24 @@
+/*
+ * Orion Operating System - Socket Diagnostics
+ *
+ * Client side of the network server's socket diagnostics (see
+ * services/net/sock_diag.h): the TCP connections and UDP endpoints of
+ * the stack with their queues, retransmission and congestion state, and
+ * the stack-wide counters orion-ss prints in its summary.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod client;
+pub mod entry;
+
+pub use client::{Filter, SockDiag};
+pub use entry::{Counters, Protocol, SocketEntry, Summary, TcpState};
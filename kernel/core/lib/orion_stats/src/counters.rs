@@ -0,0 +1,171 @@
+/*
+ * Orion Operating System - Per-CPU Counters
+ *
+ * PerCpuCounters holds N 64-bit counters for every CPU, each CPU's cells
+ * on cache lines of their own. An update is a relaxed atomic on the
+ * running CPU's cell: no lock, and no cache line bouncing between CPUs
+ * however hot the counter. Cells are still atomic so that an update
+ * racing with a migration or an interrupt is never lost.
+ *
+ * Readers aggregate on demand, summing (or taking the maximum of) every
+ * CPU's cell. The result is exact once writers are quiet and otherwise
+ * a value the counter had at some point during the read.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::cpu::{current_cpu, MAX_CPUS};
+
+#[repr(align(64))]
+struct CpuCells<const N: usize> {
+    cells: [AtomicU64; N],
+}
+
+impl<const N: usize> CpuCells<N> {
+    const fn new() -> Self {
+        Self { cells: [const { AtomicU64::new(0) }; N] }
+    }
+}
+
+pub struct PerCpuCounters<const N: usize> {
+    cpus: [CpuCells<N>; MAX_CPUS],
+}
+
+impl<const N: usize> Default for PerCpuCounters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> core::fmt::Debug for PerCpuCounters<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.snapshot()).finish()
+    }
+}
+
+impl<const N: usize> PerCpuCounters<N> {
+    pub const fn new() -> Self {
+        Self { cpus: [const { CpuCells::new() }; MAX_CPUS] }
+    }
+
+    pub fn add(&self, index: usize, value: u64) {
+        self.add_on(current_cpu(), index, value);
+    }
+
+    pub fn inc(&self, index: usize) {
+        self.add(index, 1);
+    }
+
+    /// Raise the running CPU's cell to `value`, for high-water marks and
+    /// last-event timestamps; read back with `max`
+    pub fn record_max(&self, index: usize, value: u64) {
+        self.max_on(current_cpu(), index, value);
+    }
+
+    fn add_on(&self, cpu: usize, index: usize, value: u64) {
+        self.cpus[cpu].cells[index].fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn max_on(&self, cpu: usize, index: usize, value: u64) {
+        self.cpus[cpu].cells[index].fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Total of one counter over every CPU
+    pub fn sum(&self, index: usize) -> u64 {
+        self.cpus.iter().fold(0u64, |total, cpu| total.wrapping_add(cpu.cells[index].load(Ordering::Relaxed)))
+    }
+
+    /// Largest value of one counter over every CPU
+    pub fn max(&self, index: usize) -> u64 {
+        self.cpus.iter().map(|cpu| cpu.cells[index].load(Ordering::Relaxed)).max().unwrap_or(0)
+    }
+
+    /// Every counter summed over every CPU
+    pub fn snapshot(&self) -> [u64; N] {
+        let mut totals = [0u64; N];
+        for cpu in &self.cpus {
+            for (total, cell) in totals.iter_mut().zip(&cpu.cells) {
+                *total = total.wrapping_add(cell.load(Ordering::Relaxed));
+            }
+        }
+        totals
+    }
+
+    /// Zero every counter. Updates racing with the reset may survive it
+    pub fn reset(&self) {
+        for cell in self.cpus.iter().flat_map(|cpu| &cpu.cells) {
+            cell.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn counter(&self, index: usize) -> Counter<'_, N> {
+        assert!(index < N, "counter index out of range");
+        Counter { counters: self, index }
+    }
+}
+
+/// One counter of a set, as handed out by `counter_set!` accessors
+#[derive(Clone, Copy)]
+pub struct Counter<'a, const N: usize> {
+    counters: &'a PerCpuCounters<N>,
+    index: usize,
+}
+
+impl<const N: usize> Counter<'_, N> {
+    pub fn add(&self, value: u64) {
+        self.counters.add(self.index, value);
+    }
+
+    pub fn inc(&self) {
+        self.counters.inc(self.index);
+    }
+
+    pub fn record_max(&self, value: u64) {
+        self.counters.record_max(self.index, value);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.counters.sum(self.index)
+    }
+
+    pub fn max(&self) -> u64 {
+        self.counters.max(self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregates_cpu_cells() {
+        let counters = PerCpuCounters::<3>::new();
+        counters.add_on(0, 0, 5);
+        counters.add_on(1, 0, 7);
+        counters.add_on(MAX_CPUS - 1, 2, 1);
+        counters.max_on(2, 1, 40);
+        counters.max_on(5, 1, 30);
+
+        assert_eq!(counters.sum(0), 12);
+        assert_eq!(counters.max(1), 40);
+        assert_eq!(counters.snapshot(), [12, 70, 1]);
+
+        counters.counter(2).inc();
+        assert_eq!(counters.counter(2).get(), 2);
+
+        counters.reset();
+        assert_eq!(counters.snapshot(), [0; 3]);
+    }
+
+    #[test]
+    fn test_keeps_cpus_on_separate_cache_lines() {
+        assert_eq!(core::mem::align_of::<CpuCells<1>>(), 64);
+        assert_eq!(core::mem::size_of::<CpuCells<9>>(), 128);
+    }
+}
@@ -0,0 +1,36 @@
+/*
+ * Orion Operating System - Statistics CPU Identity
+ *
+ * Counters are split by the CPU that updates them. The running CPU is
+ * given by a function the program installs once for every counter set it
+ * links; until then every update lands in CPU 0's cells, which is still
+ * correct, only shared.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// CPUs with their own cells, the kernel's limit; CPU ids wrap around beyond
+pub const MAX_CPUS: usize = 64;
+
+static CPU_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Install the function returning the id of the running CPU
+pub fn set_cpu_id(cpu_id: fn() -> usize) {
+    CPU_ID.store(cpu_id as usize, Ordering::Relaxed);
+}
+
+pub fn current_cpu() -> usize {
+    match CPU_ID.load(Ordering::Relaxed) {
+        0 => 0,
+        function => {
+            let cpu_id: fn() -> usize = unsafe { core::mem::transmute(function) };
+            cpu_id() % MAX_CPUS
+        }
+    }
+}
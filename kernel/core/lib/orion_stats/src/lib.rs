@@ -0,0 +1,31 @@
+/*
+ * Orion Operating System - Per-CPU Statistics
+ *
+ * Lock-free statistics for drivers on SMP systems. A shared AtomicU64
+ * counter bounces its cache line between every CPU that updates it; the
+ * counters here give each CPU cells of its own, aggregated only when
+ * someone reads them, and turn successive aggregates into rates.
+ *
+ * Drivers declare their counters with counter_set! and install the CPU
+ * id function once at startup:
+ *
+ *     orion_stats::set_cpu_id(sys_current_cpu);
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+pub mod counters;
+pub mod cpu;
+pub mod rate;
+
+mod set;
+
+pub use counters::{Counter, PerCpuCounters};
+pub use cpu::{current_cpu, set_cpu_id, MAX_CPUS};
+pub use rate::{Interval, RateSampler};
@@ -0,0 +1,104 @@
+/*
+ * Orion Operating System - Counter Rates
+ *
+ * RateSampler keeps the previous aggregate of a counter set with its
+ * timestamp and turns each new one into the counts of the interval
+ * since, from which per-second rates follow. A counter that went
+ * backwards was reset in between and counts from zero.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Counts over one sampling interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval<const N: usize> {
+    pub interval_ns: u64,
+    pub deltas: [u64; N],
+}
+
+impl<const N: usize> Interval<N> {
+    /// Per-second rate of `count` events over the interval
+    pub fn per_second(&self, count: u64) -> u64 {
+        if self.interval_ns == 0 {
+            return 0;
+        }
+        (count as u128 * NANOS_PER_SECOND / self.interval_ns as u128) as u64
+    }
+
+    /// Per-second rate of every counter
+    pub fn rates(&self) -> [u64; N] {
+        self.deltas.map(|delta| self.per_second(delta))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateSampler<const N: usize> {
+    previous: Option<([u64; N], u64)>,
+    last: Option<Interval<N>>,
+}
+
+impl<const N: usize> Default for RateSampler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RateSampler<N> {
+    pub const fn new() -> Self {
+        Self { previous: None, last: None }
+    }
+
+    /// Record an aggregate taken at `now_ns`; returns the interval since
+    /// the previous one, None for the first sample or a clock that did not
+    /// move
+    pub fn sample(&mut self, values: [u64; N], now_ns: u64) -> Option<Interval<N>> {
+        let previous = self.previous.replace((values, now_ns));
+        let interval = match previous {
+            Some((earlier, then)) if now_ns > then => {
+                let mut deltas = values;
+                for (delta, &before) in deltas.iter_mut().zip(&earlier) {
+                    if *delta >= before {
+                        *delta -= before;
+                    }
+                }
+                Some(Interval { interval_ns: now_ns - then, deltas })
+            }
+            _ => None,
+        };
+        if interval.is_some() {
+            self.last = interval;
+        }
+        interval
+    }
+
+    /// The most recent interval
+    pub fn last(&self) -> Option<&Interval<N>> {
+        self.last.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_computes_rates_across_resets() {
+        let mut sampler = RateSampler::<2>::new();
+        assert_eq!(sampler.sample([100, 10], 1_000_000_000), None);
+
+        let interval = sampler.sample([300, 4], 3_000_000_000).unwrap();
+        assert_eq!(interval.interval_ns, 2_000_000_000);
+        // The second counter was reset and counted 4 since
+        assert_eq!(interval.deltas, [200, 4]);
+        assert_eq!(interval.rates(), [100, 2]);
+
+        assert_eq!(sampler.sample([300, 4], 3_000_000_000), None);
+        assert_eq!(sampler.last(), Some(&interval));
+    }
+}
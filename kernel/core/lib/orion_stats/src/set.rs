@@ -0,0 +1,141 @@
+/*
+ * Orion Operating System - Named Counter Sets
+ *
+ * counter_set! turns a list of counter names into a PerCpuCounters-backed
+ * statistics structure with one accessor per counter, and a plain Copy
+ * structure of the same names holding aggregated values:
+ *
+ *     orion_stats::counter_set! {
+ *         pub struct RxCounters => RxValues {
+ *             /// Frames received
+ *             packets,
+ *             bytes,
+ *         }
+ *     }
+ *
+ *     stats.packets().inc();
+ *     let values: RxValues = stats.snapshot();
+ *
+ * Counter names must not clash with the generated methods (new, snapshot,
+ * values, reset).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#[macro_export]
+macro_rules! counter_set {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident => $values:ident {
+            $( $(#[$field_attr:meta])* $field:ident ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            counters: $crate::PerCpuCounters<{ $values::COUNT }>,
+        }
+
+        #[doc = concat!("Aggregated values of [`", stringify!($name), "`]")]
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        #[repr(C)]
+        $vis struct $values {
+            $( $(#[$field_attr])* pub $field: u64, )*
+        }
+
+        impl $values {
+            pub const COUNT: usize = [$(stringify!($field)),*].len();
+            pub const NAMES: [&'static str; Self::COUNT] = [$(stringify!($field)),*];
+
+            /// Values in declaration order
+            pub fn from_array(values: [u64; Self::COUNT]) -> Self {
+                let [$($field),*] = values;
+                Self { $($field),* }
+            }
+
+            pub fn to_array(self) -> [u64; Self::COUNT] {
+                [$(self.$field),*]
+            }
+        }
+
+        impl $name {
+            pub const fn new() -> Self {
+                Self { counters: $crate::PerCpuCounters::new() }
+            }
+
+            $(
+                $(#[$field_attr])*
+                pub fn $field(&self) -> $crate::Counter<'_, { $values::COUNT }> {
+                    self.counters.counter(::core::mem::offset_of!($values, $field) / ::core::mem::size_of::<u64>())
+                }
+            )*
+
+            /// Every counter summed over every CPU
+            pub fn snapshot(&self) -> $values {
+                $values::from_array(self.counters.snapshot())
+            }
+
+            /// The same in declaration order, for a RateSampler
+            pub fn values(&self) -> [u64; $values::COUNT] {
+                self.counters.snapshot()
+            }
+
+            pub fn reset(&self) {
+                self.counters.reset();
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Debug::fmt(&self.snapshot(), f)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RateSampler;
+
+    counter_set! {
+        /// Test set
+        struct TestCounters => TestValues {
+            /// Frames
+            packets,
+            bytes,
+            last_seen,
+        }
+    }
+
+    #[test]
+    fn test_names_counters() {
+        static STATS: TestCounters = TestCounters::new();
+        STATS.packets().inc();
+        STATS.bytes().add(1500);
+        STATS.last_seen().record_max(42);
+        STATS.last_seen().record_max(7);
+
+        let values = STATS.snapshot();
+        assert_eq!(values, TestValues { packets: 1, bytes: 1500, last_seen: 42 });
+        assert_eq!(TestValues::NAMES, ["packets", "bytes", "last_seen"]);
+        assert_eq!(TestValues::from_array(values.to_array()), values);
+
+        let mut sampler = RateSampler::new();
+        sampler.sample(STATS.values(), 0);
+        STATS.bytes().add(500);
+        let rates = TestValues::from_array(sampler.sample(STATS.values(), 500_000_000).unwrap().rates());
+        assert_eq!(rates.bytes, 1000);
+
+        STATS.reset();
+        assert_eq!(STATS.snapshot(), TestValues::default());
+    }
+}
@@ -0,0 +1,31 @@
+/*
+ * Orion Operating System - Storage Policies
+ *
+ * What a dataset asks of the storage under it, written down once and
+ * enforced by the storage manager rather than remembered by whoever
+ * created the volume: how many copies it keeps, which device classes
+ * and nodes they may live on, how often it is snapshotted and for how
+ * long snapshots are kept, and whether it must be encrypted.
+ *
+ * Policies are short texts (see policy.rs), checked before they are
+ * accepted. A volume created under a policy is placed by it and refused
+ * when no placement or key meets it; afterwards the manager compares the
+ * volume with its policy from time to time and corrects the drift it can
+ * (see reconcile.rs), reporting what it cannot.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod policy;
+pub mod reconcile;
+
+pub use policy::{DeviceClass, Encryption, Placement, PolicyError, SnapshotSchedule, StoragePolicy};
+pub use reconcile::{admit, place, reconcile, Action, Dataset, Device, Reconciliation, Snapshot, Violation};
@@ -0,0 +1,326 @@
+/*
+ * Orion Operating System - Storage Policy Rules
+ *
+ * A policy is a few lines of `keyword arguments`, blank lines and `#`
+ * comments ignored:
+ *
+ *   policy gold
+ *   copies 2
+ *   classes ssd nvme
+ *   nodes rack-a rack-b rack-c
+ *   spread nodes
+ *   snapshots every 1h keep 24 max-age 2d
+ *   encryption required 256
+ *
+ * `policy` names it and is the only line required. `copies` (1 to
+ * MAX_COPIES, default 1) is the redundancy; `classes` and `nodes` limit
+ * the devices copies may be placed on, any when left out, and `spread
+ * nodes` keeps copies on distinct nodes. `snapshots` takes one every
+ * interval and keeps the latest `keep`, and none older than `max-age`
+ * when given; durations are a number followed by s, m, h or d.
+ * `encryption required` wants the data encrypted with a key of at least
+ * the given bits (128 or 256); `encryption optional` is the default.
+ *
+ * Parsing checks the policy as a whole as well, so a policy that parses
+ * can be met by some set of devices.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Most copies a policy may ask for
+pub const MAX_COPIES: u8 = 4;
+
+/// Longest policy name
+pub const MAX_NAME: usize = 64;
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceClass {
+    Hdd,
+    Ssd,
+    Nvme,
+    /// Reached over the network (NBD, iSCSI)
+    Remote,
+}
+
+impl DeviceClass {
+    pub const ALL: [DeviceClass; 4] = [DeviceClass::Hdd, DeviceClass::Ssd, DeviceClass::Nvme, DeviceClass::Remote];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DeviceClass::Hdd => "hdd",
+            DeviceClass::Ssd => "ssd",
+            DeviceClass::Nvme => "nvme",
+            DeviceClass::Remote => "remote",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.name() == name)
+    }
+
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_u32(value: u32) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+}
+
+/// Where copies may live
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Placement {
+    /// Allowed device classes, any when empty
+    pub classes: Vec<DeviceClass>,
+    /// Allowed nodes, any when empty
+    pub nodes: Vec<String>,
+    /// Each copy on a node of its own
+    pub spread: bool,
+}
+
+impl Placement {
+    pub fn allows(&self, class: DeviceClass, node: &str) -> bool {
+        (self.classes.is_empty() || self.classes.contains(&class))
+            && (self.nodes.is_empty() || self.nodes.iter().any(|allowed| allowed == node))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotSchedule {
+    pub every_ns: u64,
+    /// Snapshots kept, the latest ones
+    pub keep: u32,
+    /// Snapshots older than this are dropped, 0 for no limit
+    pub max_age_ns: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    Optional,
+    Required { min_key_bits: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePolicy {
+    pub name: String,
+    pub copies: u8,
+    pub placement: Placement,
+    pub snapshots: Option<SnapshotSchedule>,
+    pub encryption: Encryption,
+}
+
+/// Why a policy text was refused; `line` is 1-based, 0 for the policy
+/// as a whole
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyError {
+    pub line: usize,
+    pub message: String,
+}
+
+fn error(line: usize, message: &str) -> PolicyError {
+    PolicyError { line, message: message.to_string() }
+}
+
+/// `30s`, `15m`, `1h`, `7d`
+fn parse_duration(text: &str) -> Option<u64> {
+    let unit = match text.as_bytes().last()? {
+        b's' => 1,
+        b'm' => 60,
+        b'h' => 3600,
+        b'd' => 86400,
+        _ => return None,
+    };
+    let value: u64 = text[..text.len() - 1].parse().ok()?;
+    value.checked_mul(unit)?.checked_mul(NS_PER_SEC)
+}
+
+fn format_duration(ns: u64) -> String {
+    let seconds = ns / NS_PER_SEC;
+    for (unit, length) in [("d", 86400), ("h", 3600), ("m", 60)] {
+        if seconds >= length && seconds.is_multiple_of(length) {
+            return format!("{}{}", seconds / length, unit);
+        }
+    }
+    format!("{}s", seconds)
+}
+
+fn parse_snapshots(words: &[&str], line: usize) -> Result<SnapshotSchedule, PolicyError> {
+    let mut schedule = SnapshotSchedule { every_ns: 0, keep: 0, max_age_ns: 0 };
+    for pair in words.chunks(2) {
+        let [key, value] = pair else {
+            return Err(error(line, "snapshot setting without a value"));
+        };
+        match *key {
+            "every" => schedule.every_ns = parse_duration(value).ok_or_else(|| error(line, "bad duration"))?,
+            "keep" => schedule.keep = value.parse().map_err(|_| error(line, "bad snapshot count"))?,
+            "max-age" => schedule.max_age_ns = parse_duration(value).ok_or_else(|| error(line, "bad duration"))?,
+            _ => return Err(error(line, "unknown snapshot setting")),
+        }
+    }
+    if schedule.every_ns == 0 || schedule.keep == 0 {
+        return Err(error(line, "snapshots need a non-zero interval and count"));
+    }
+    if schedule.max_age_ns != 0 && schedule.max_age_ns < schedule.every_ns {
+        return Err(error(line, "max-age shorter than the snapshot interval"));
+    }
+    Ok(schedule)
+}
+
+impl StoragePolicy {
+    pub fn parse(text: &str) -> Result<StoragePolicy, PolicyError> {
+        let mut name = None;
+        let mut policy = StoragePolicy {
+            name: String::new(),
+            copies: 1,
+            placement: Placement::default(),
+            snapshots: None,
+            encryption: Encryption::Optional,
+        };
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let content = raw.split('#').next().unwrap_or("");
+            let words: Vec<&str> = content.split_whitespace().collect();
+            let Some((&keyword, args)) = words.split_first() else {
+                continue;
+            };
+            match (keyword, args) {
+                ("policy", [value]) => {
+                    let valid = value.len() <= MAX_NAME
+                        && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"-_.".contains(&byte));
+                    if !valid || name.is_some() {
+                        return Err(error(line, "bad or repeated policy name"));
+                    }
+                    name = Some(value.to_string());
+                }
+                ("copies", [value]) => {
+                    policy.copies = match value.parse() {
+                        Ok(copies) if (1..=MAX_COPIES).contains(&copies) => copies,
+                        _ => return Err(error(line, "copies out of range")),
+                    };
+                }
+                ("classes", classes) if !classes.is_empty() => {
+                    for class in classes {
+                        let class = DeviceClass::from_name(class).ok_or_else(|| error(line, "unknown device class"))?;
+                        if !policy.placement.classes.contains(&class) {
+                            policy.placement.classes.push(class);
+                        }
+                    }
+                }
+                ("nodes", nodes) if !nodes.is_empty() => {
+                    for node in nodes {
+                        if !policy.placement.nodes.iter().any(|known| known == node) {
+                            policy.placement.nodes.push(node.to_string());
+                        }
+                    }
+                }
+                ("spread", ["nodes"]) => policy.placement.spread = true,
+                ("snapshots", settings) => policy.snapshots = Some(parse_snapshots(settings, line)?),
+                ("encryption", ["optional"]) => policy.encryption = Encryption::Optional,
+                ("encryption", ["required", bits]) => {
+                    policy.encryption = match *bits {
+                        "128" => Encryption::Required { min_key_bits: 128 },
+                        "256" => Encryption::Required { min_key_bits: 256 },
+                        _ => return Err(error(line, "key size must be 128 or 256")),
+                    };
+                }
+                _ => return Err(error(line, "unknown or malformed rule")),
+            }
+        }
+        policy.name = name.ok_or_else(|| error(0, "policy without a name"))?;
+        let nodes = policy.placement.nodes.len();
+        if policy.placement.spread && nodes > 0 && nodes < policy.copies as usize {
+            return Err(error(0, "fewer allowed nodes than spread copies"));
+        }
+        Ok(policy)
+    }
+
+    /// The policy as text, which parses back to the same policy
+    pub fn to_text(&self) -> String {
+        let mut text = format!("policy {}\ncopies {}\n", self.name, self.copies);
+        if !self.placement.classes.is_empty() {
+            let classes: Vec<&str> = self.placement.classes.iter().map(|class| class.name()).collect();
+            let _ = writeln!(text, "classes {}", classes.join(" "));
+        }
+        if !self.placement.nodes.is_empty() {
+            let _ = writeln!(text, "nodes {}", self.placement.nodes.join(" "));
+        }
+        if self.placement.spread {
+            text.push_str("spread nodes\n");
+        }
+        if let Some(schedule) = self.snapshots {
+            let _ = write!(text, "snapshots every {} keep {}", format_duration(schedule.every_ns), schedule.keep);
+            if schedule.max_age_ns != 0 {
+                let _ = write!(text, " max-age {}", format_duration(schedule.max_age_ns));
+            }
+            text.push('\n');
+        }
+        if let Encryption::Required { min_key_bits } = self.encryption {
+            let _ = writeln!(text, "encryption required {}", min_key_bits);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOLD: &str = "# replicated, hourly snapshots\n\
+                        policy gold\n\
+                        copies 2\n\
+                        classes ssd nvme ssd\n\
+                        nodes rack-a rack-b\n\
+                        spread nodes\n\
+                        \n\
+                        snapshots every 1h keep 24 max-age 2d\n\
+                        encryption required 256  # customer data\n";
+
+    #[test]
+    fn test_parses_and_renders_policies() {
+        let gold = StoragePolicy::parse(GOLD).unwrap();
+        assert_eq!(gold.copies, 2);
+        assert_eq!(gold.placement.classes, [DeviceClass::Ssd, DeviceClass::Nvme]);
+        assert!(gold.placement.spread && gold.placement.allows(DeviceClass::Nvme, "rack-b"));
+        assert!(!gold.placement.allows(DeviceClass::Hdd, "rack-a") && !gold.placement.allows(DeviceClass::Ssd, "x"));
+        assert_eq!(
+            gold.snapshots,
+            Some(SnapshotSchedule { every_ns: 3600 * NS_PER_SEC, keep: 24, max_age_ns: 2 * 86400 * NS_PER_SEC })
+        );
+        assert_eq!(gold.encryption, Encryption::Required { min_key_bits: 256 });
+        assert_eq!(StoragePolicy::parse(&gold.to_text()), Ok(gold));
+
+        let plain = StoragePolicy::parse("policy scratch").unwrap();
+        assert_eq!((plain.copies, plain.snapshots, plain.encryption), (1, None, Encryption::Optional));
+        assert!(plain.placement.allows(DeviceClass::Remote, "anywhere"));
+    }
+
+    #[test]
+    fn test_refuses_bad_policies() {
+        for (text, line) in [
+            ("copies 2", 0),
+            ("policy a\ncopies 9", 2),
+            ("policy a\nclasses tape", 2),
+            ("policy a\nsnapshots every 1h", 2),
+            ("policy a\nsnapshots every 2h keep 3 max-age 1h", 2),
+            ("policy a\nsnapshots every 1w keep 3", 2),
+            ("policy a\nencryption required 512", 2),
+            ("policy a\ncopies 3\nnodes x y\nspread nodes", 0),
+            ("policy a\npolicy b", 2),
+            ("policy a b", 1),
+            ("policy a\nmirror yes", 2),
+        ] {
+            assert_eq!(StoragePolicy::parse(text).map_err(|error| error.line), Err(line), "{}", text);
+        }
+    }
+}
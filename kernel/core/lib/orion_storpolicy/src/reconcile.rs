@@ -0,0 +1,348 @@
+/*
+ * Orion Operating System - Policy Enforcement
+ *
+ * Placement picks the devices a new dataset's copies go to: online
+ * devices the policy allows with room for the whole dataset, the
+ * emptiest first, one per node when the policy spreads them. A dataset
+ * is admitted only when every copy finds a device and its key meets the
+ * encryption requirement.
+ *
+ * Reconciliation compares a dataset with its policy and answers what to
+ * do about the difference:
+ *
+ *   - copies lost with their device (missing or offline) or sitting on a
+ *     device the policy no longer allows are replaced by new ones placed
+ *     as above; the old ones are only dropped once enough healthy copies
+ *     exist, so redundancy never goes down on the way
+ *   - copies beyond the policy's count are dropped
+ *   - a snapshot is taken when the latest one is an interval old, and
+ *     the oldest ones are expired past the count or the age to keep
+ *
+ * What it cannot fix is reported as a violation: too few devices to
+ * hold the copies, or a dataset encrypted more weakly than required,
+ * which takes re-encrypting the data and is left to an administrator.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use crate::policy::{DeviceClass, Encryption, StoragePolicy};
+
+/// A device copies may be placed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub name: String,
+    pub class: DeviceClass,
+    pub node: String,
+    /// Bytes free
+    pub free: u64,
+    pub online: bool,
+}
+
+/// A snapshot taken for the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub name: String,
+    pub created_ns: u64,
+}
+
+/// What a dataset is now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dataset {
+    pub name: String,
+    pub size: u64,
+    /// Devices holding a copy
+    pub replicas: Vec<String>,
+    /// Key size of its encryption, 0 when stored in clear
+    pub key_bits: u32,
+    /// Snapshots taken under the policy; others are not its business
+    pub snapshots: Vec<Snapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    AddReplica { device: String },
+    RemoveReplica { device: String },
+    TakeSnapshot,
+    ExpireSnapshot { name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Not enough devices meet the policy for a new dataset's copies
+    NoPlacement { placed: u8, needed: u8 },
+    /// Fewer healthy copies than wanted, and nowhere to add one
+    Degraded { healthy: u8, needed: u8 },
+    /// A copy on a device the policy does not allow, kept until it can be
+    /// replaced
+    Misplaced { device: String },
+    /// Encrypted with a shorter key than required, 0 bits when not at all
+    Unencrypted { key_bits: u32, required: u32 },
+}
+
+impl Violation {
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            Violation::NoPlacement { .. } => 1,
+            Violation::Degraded { .. } => 2,
+            Violation::Misplaced { .. } => 3,
+            Violation::Unencrypted { .. } => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub actions: Vec<Action>,
+    pub violations: Vec<Violation>,
+}
+
+impl Reconciliation {
+    pub fn is_compliant(&self) -> bool {
+        self.actions.is_empty() && self.violations.is_empty()
+    }
+}
+
+/// Up to `count` more devices for copies of `size` bytes, other than
+/// `taken` and, when spreading, on none of the `nodes` already used
+fn choose<'a>(
+    policy: &StoragePolicy,
+    devices: &'a [Device],
+    size: u64,
+    count: usize,
+    taken: &[&str],
+    mut nodes: Vec<&'a str>,
+) -> Vec<String> {
+    let mut candidates: Vec<&Device> = devices
+        .iter()
+        .filter(|device| device.online && device.free >= size && policy.placement.allows(device.class, &device.node))
+        .filter(|device| !taken.contains(&device.name.as_str()))
+        .collect();
+    candidates.sort_by(|a, b| b.free.cmp(&a.free).then_with(|| a.name.cmp(&b.name)));
+
+    let mut chosen = Vec::new();
+    for device in candidates {
+        if chosen.len() == count {
+            break;
+        }
+        if policy.placement.spread && nodes.contains(&device.node.as_str()) {
+            continue;
+        }
+        nodes.push(&device.node);
+        chosen.push(device.name.clone());
+    }
+    chosen
+}
+
+/// Devices for the copies of a new dataset
+pub fn place(policy: &StoragePolicy, devices: &[Device], size: u64) -> Result<Vec<String>, Violation> {
+    let chosen = choose(policy, devices, size, policy.copies as usize, &[], Vec::new());
+    if chosen.len() < policy.copies as usize {
+        return Err(Violation::NoPlacement { placed: chosen.len() as u8, needed: policy.copies });
+    }
+    Ok(chosen)
+}
+
+fn encryption_violation(policy: &StoragePolicy, key_bits: u32) -> Option<Violation> {
+    match policy.encryption {
+        Encryption::Required { min_key_bits } if key_bits < min_key_bits => {
+            Some(Violation::Unencrypted { key_bits, required: min_key_bits })
+        }
+        _ => None,
+    }
+}
+
+/// Check a dataset about to be created and place its copies
+pub fn admit(policy: &StoragePolicy, devices: &[Device], size: u64, key_bits: u32) -> Result<Vec<String>, Violation> {
+    if let Some(violation) = encryption_violation(policy, key_bits) {
+        return Err(violation);
+    }
+    place(policy, devices, size)
+}
+
+pub fn reconcile(policy: &StoragePolicy, dataset: &Dataset, devices: &[Device], now_ns: u64) -> Reconciliation {
+    let mut result = Reconciliation::default();
+    let needed = policy.copies as usize;
+
+    let mut healthy: Vec<&Device> = Vec::new();
+    // Lost or misplaced, and whether misplaced
+    let mut unhealthy: Vec<(&str, bool)> = Vec::new();
+    for replica in &dataset.replicas {
+        match devices.iter().find(|device| &device.name == replica) {
+            Some(device) if device.online => {
+                let spread_clash = policy.placement.spread && healthy.iter().any(|other| other.node == device.node);
+                if policy.placement.allows(device.class, &device.node) && !spread_clash {
+                    healthy.push(device);
+                } else {
+                    unhealthy.push((replica, true));
+                }
+            }
+            _ => unhealthy.push((replica, false)),
+        }
+    }
+
+    for extra in healthy.iter().skip(needed) {
+        result.actions.push(Action::RemoveReplica { device: extra.name.clone() });
+    }
+    let mut copies = healthy.len().min(needed);
+    if copies < needed {
+        // Devices holding a copy of any kind are not candidates
+        let taken: Vec<&str> = dataset.replicas.iter().map(String::as_str).collect();
+        let nodes = healthy.iter().map(|device| device.node.as_str()).collect();
+        for device in choose(policy, devices, dataset.size, needed - copies, &taken, nodes) {
+            result.actions.push(Action::AddReplica { device });
+            copies += 1;
+        }
+    }
+    if copies < needed {
+        result.violations.push(Violation::Degraded { healthy: copies as u8, needed: policy.copies });
+    }
+    for (device, misplaced) in unhealthy {
+        if copies >= needed {
+            result.actions.push(Action::RemoveReplica { device: String::from(device) });
+        } else if misplaced {
+            result.violations.push(Violation::Misplaced { device: String::from(device) });
+        }
+    }
+
+    if let Some(schedule) = policy.snapshots {
+        let mut snapshots: Vec<&Snapshot> = dataset.snapshots.iter().collect();
+        snapshots.sort_by_key(|snapshot| Reverse(snapshot.created_ns));
+        let due = snapshots.first().is_none_or(|latest| now_ns.saturating_sub(latest.created_ns) >= schedule.every_ns);
+        // Room for the one about to be taken
+        let keep = schedule.keep as usize - due as usize;
+        if due {
+            result.actions.push(Action::TakeSnapshot);
+        }
+        for (index, snapshot) in snapshots.into_iter().enumerate() {
+            let too_old = schedule.max_age_ns != 0 && now_ns.saturating_sub(snapshot.created_ns) > schedule.max_age_ns;
+            if index >= keep || too_old {
+                result.actions.push(Action::ExpireSnapshot { name: snapshot.name.clone() });
+            }
+        }
+    }
+
+    if let Some(violation) = encryption_violation(policy, dataset.key_bits) {
+        result.violations.push(violation);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    const HOUR: u64 = 3600 * 1_000_000_000;
+
+    fn device(name: &str, class: DeviceClass, node: &str, free: u64) -> Device {
+        Device { name: name.to_string(), class, node: node.to_string(), free, online: true }
+    }
+
+    fn devices() -> Vec<Device> {
+        vec![
+            device("a1", DeviceClass::Ssd, "a", 100),
+            device("a2", DeviceClass::Nvme, "a", 300),
+            device("b1", DeviceClass::Ssd, "b", 200),
+            device("c1", DeviceClass::Hdd, "c", 900),
+            device("d1", DeviceClass::Nvme, "d", 10),
+        ]
+    }
+
+    fn gold() -> StoragePolicy {
+        StoragePolicy::parse("policy gold\ncopies 2\nclasses ssd nvme\nspread nodes\nencryption required 256").unwrap()
+    }
+
+    #[test]
+    fn test_places_and_admits_new_datasets() {
+        let devices = devices();
+        // Emptiest first, one per node
+        assert_eq!(admit(&gold(), &devices, 50, 256), Ok(vec!["a2".to_string(), "b1".to_string()]));
+        assert_eq!(admit(&gold(), &devices, 50, 0), Err(Violation::Unencrypted { key_bits: 0, required: 256 }));
+        assert_eq!(place(&gold(), &devices, 250), Err(Violation::NoPlacement { placed: 1, needed: 2 }));
+        let any = StoragePolicy::parse("policy any\ncopies 3").unwrap();
+        assert_eq!(place(&any, &devices, 50).unwrap(), ["c1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn test_repairs_drift_without_losing_redundancy() {
+        let mut devices = devices();
+        let mut dataset = Dataset {
+            name: "db".to_string(),
+            size: 50,
+            replicas: vec!["a2".to_string(), "b1".to_string()],
+            key_bits: 256,
+            snapshots: vec![],
+        };
+        assert!(reconcile(&gold(), &dataset, &devices, 0).is_compliant());
+
+        // b1 fails: a copy goes to the only other allowed node with room
+        devices[2].online = false;
+        devices.push(device("e1", DeviceClass::Ssd, "e", 100));
+        let plan = reconcile(&gold(), &dataset, &devices, 0);
+        let add = Action::AddReplica { device: "e1".to_string() };
+        assert_eq!(plan.actions, [add, Action::RemoveReplica { device: "b1".to_string() }]);
+
+        // A copy on a disallowed class stays until it can be replaced
+        devices.pop();
+        dataset.replicas = vec!["a2".to_string(), "c1".to_string(), "a1".to_string()];
+        let plan = reconcile(&gold(), &dataset, &devices, 0);
+        assert_eq!(plan.actions, []);
+        assert_eq!(
+            plan.violations,
+            [
+                Violation::Degraded { healthy: 1, needed: 2 },
+                Violation::Misplaced { device: "c1".to_string() },
+                Violation::Misplaced { device: "a1".to_string() },
+            ]
+        );
+        dataset.key_bits = 128;
+        let plan = reconcile(&gold(), &dataset, &devices, 0);
+        assert_eq!(plan.violations.last(), Some(&Violation::Unencrypted { key_bits: 128, required: 256 }));
+
+        let single = StoragePolicy::parse("policy single").unwrap();
+        let plan = reconcile(&single, &dataset, &devices, 0);
+        assert_eq!(
+            plan.actions,
+            [Action::RemoveReplica { device: "c1".to_string() }, Action::RemoveReplica { device: "a1".to_string() }]
+        );
+    }
+
+    #[test]
+    fn test_takes_and_expires_snapshots() {
+        let policy = StoragePolicy::parse("policy hourly\nsnapshots every 1h keep 3 max-age 1d").unwrap();
+        let snapshot = |name: &str, hours: u64| Snapshot { name: name.to_string(), created_ns: hours * HOUR };
+        let mut dataset = Dataset {
+            name: "db".to_string(),
+            size: 1,
+            replicas: vec!["c1".to_string()],
+            key_bits: 0,
+            snapshots: vec![],
+        };
+        let devices = devices();
+        assert_eq!(reconcile(&policy, &dataset, &devices, 0).actions, [Action::TakeSnapshot]);
+
+        dataset.snapshots = vec![snapshot("s1", 30), snapshot("s4", 33), snapshot("s3", 32), snapshot("s2", 31)];
+        // Not due yet: the latest three stay
+        let expire = |name: &str| Action::ExpireSnapshot { name: name.to_string() };
+        assert_eq!(reconcile(&policy, &dataset, &devices, 33 * HOUR + 1).actions, [expire("s1")]);
+        // Due: room for the new one
+        assert_eq!(
+            reconcile(&policy, &dataset, &devices, 34 * HOUR).actions,
+            [Action::TakeSnapshot, expire("s2"), expire("s1")]
+        );
+        // A day later everything is too old, but a new one comes first
+        assert_eq!(
+            reconcile(&policy, &dataset, &devices, 60 * HOUR).actions,
+            [Action::TakeSnapshot, expire("s4"), expire("s3"), expire("s2"), expire("s1")]
+        );
+    }
+}
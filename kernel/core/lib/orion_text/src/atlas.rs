@@ -0,0 +1,218 @@
+/*
+ * Orion Operating System - Glyph Atlas
+ *
+ * Rasterized glyphs are cached in a single 8-bit coverage texture, packed
+ * on shelves: a glyph goes on the first shelf tall enough with room left,
+ * or opens a new shelf below the last. Glyphs are separated by one pixel
+ * so that scaled sampling never bleeds. When the texture is full it is
+ * cleared and refilled from the glyphs in use, which is cheap next to
+ * tracking the lifetime of every entry.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::raster::GlyphBitmap;
+
+const PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GlyphKey {
+    pub font: u32,
+    pub glyph: u32,
+    pub size: u32,
+}
+
+/// Glyph position in the texture; `left` and `top` as in GlyphBitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasEntry {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub left: i32,
+    pub top: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AtlasStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Times the texture was cleared for lack of space
+    pub resets: u64,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    used: u32,
+}
+
+pub struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    texture: Vec<u8>,
+    shelves: Vec<Shelf>,
+    // None for glyphs that draw nothing, so they are not rasterized again
+    entries: BTreeMap<GlyphKey, Option<AtlasEntry>>,
+    stats: AtlasStats,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            texture: vec![0; (width * height) as usize],
+            shelves: Vec::new(),
+            entries: BTreeMap::new(),
+            stats: AtlasStats::default(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Coverage texture, `width` bytes per row
+    pub fn texture(&self) -> &[u8] {
+        &self.texture
+    }
+
+    pub fn stats(&self) -> AtlasStats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Coverage of texture pixel (x, y) of an entry
+    pub fn coverage(&self, entry: &AtlasEntry, x: u32, y: u32) -> u8 {
+        self.texture[((entry.y + y) * self.width + entry.x + x) as usize]
+    }
+
+    pub fn clear(&mut self) {
+        self.texture.fill(0);
+        self.shelves.clear();
+        self.entries.clear();
+    }
+
+    /// Cached glyph, rasterizing and packing it on a miss. None if the
+    /// glyph draws nothing or cannot fit even in an empty texture.
+    pub fn get_or_insert<F>(&mut self, key: GlyphKey, rasterize: F) -> Option<AtlasEntry>
+    where
+        F: FnOnce() -> Option<GlyphBitmap>,
+    {
+        if let Some(&entry) = self.entries.get(&key) {
+            self.stats.hits += 1;
+            return entry;
+        }
+        self.stats.misses += 1;
+        let entry = rasterize().and_then(|bitmap| self.insert(&bitmap));
+        self.entries.insert(key, entry);
+        entry
+    }
+
+    fn insert(&mut self, bitmap: &GlyphBitmap) -> Option<AtlasEntry> {
+        let (width, height) = (bitmap.width + PADDING, bitmap.height + PADDING);
+        if width > self.width || height > self.height {
+            return None;
+        }
+        let (x, y) = match self.allocate(width, height) {
+            Some(position) => position,
+            None => {
+                // Entries already handed out stay valid until the caller
+                // asks again, so only the cache is dropped
+                self.clear();
+                self.stats.resets += 1;
+                self.allocate(width, height)?
+            }
+        };
+        for row in 0..bitmap.height {
+            let source = (row * bitmap.width) as usize;
+            let target = ((y + row) * self.width + x) as usize;
+            self.texture[target..target + bitmap.width as usize]
+                .copy_from_slice(&bitmap.coverage[source..source + bitmap.width as usize]);
+        }
+        Some(AtlasEntry { x, y, width: bitmap.width, height: bitmap.height, left: bitmap.left, top: bitmap.top })
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        // Tightest shelf that fits, to keep tall shelves for tall glyphs
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && self.width - shelf.used >= width)
+            .min_by_key(|shelf| shelf.height);
+        if let Some(shelf) = shelf {
+            let x = shelf.used;
+            shelf.used += width;
+            return Some((x, shelf.y));
+        }
+        let y = self.shelves.last().map_or(0, |shelf| shelf.y + shelf.height);
+        if self.height - y < height {
+            return None;
+        }
+        self.shelves.push(Shelf { y, height, used: width });
+        Some((0, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(width: u32, height: u32) -> GlyphBitmap {
+        GlyphBitmap { width, height, left: 1, top: height as i32, coverage: vec![200; (width * height) as usize] }
+    }
+
+    fn key(glyph: u32) -> GlyphKey {
+        GlyphKey { font: 0, glyph, size: 16 }
+    }
+
+    #[test]
+    fn test_packs_shelves() {
+        let mut atlas = GlyphAtlas::new(16, 16);
+        let first = atlas.get_or_insert(key(1), || Some(bitmap(6, 7))).unwrap();
+        let second = atlas.get_or_insert(key(2), || Some(bitmap(6, 4))).unwrap();
+        let third = atlas.get_or_insert(key(3), || Some(bitmap(6, 4))).unwrap();
+        assert_eq!((first.x, first.y, second.x, second.y), (0, 0, 7, 0));
+        // No room left on the first shelf: a new one below it
+        assert_eq!((third.x, third.y), (0, 8));
+        assert_eq!((atlas.coverage(&second, 5, 3), atlas.texture()[7 + 6]), (200, 0));
+
+        // Cached, including glyphs without pixels
+        assert_eq!(atlas.get_or_insert(key(1), || unreachable!()), Some(first));
+        assert_eq!(atlas.get_or_insert(key(4), || None), None);
+        assert_eq!(atlas.get_or_insert(key(4), || unreachable!()), None);
+        assert_eq!(atlas.stats(), AtlasStats { hits: 2, misses: 4, resets: 0 });
+        assert!(atlas.get_or_insert(key(5), || Some(bitmap(16, 2))).is_none());
+    }
+
+    #[test]
+    fn test_resets_when_full() {
+        let mut atlas = GlyphAtlas::new(16, 16);
+        for glyph in 0..4 {
+            atlas.get_or_insert(key(glyph), || Some(bitmap(7, 7))).unwrap();
+        }
+        let entry = atlas.get_or_insert(key(9), || Some(bitmap(7, 7))).unwrap();
+        assert_eq!((entry.x, entry.y, atlas.len()), (0, 0, 1));
+        assert_eq!(atlas.stats().resets, 1);
+    }
+}
@@ -0,0 +1,234 @@
+/*
+ * Orion Operating System - Fonts
+ *
+ * One interface over bitmap (PSF) and outline (TrueType) fonts, in pixel
+ * units at a requested size. Outline fonts scale freely; bitmap fonts are
+ * magnified by whole multiples of their cell height, which keeps console
+ * text crisp on high resolution outputs. Font files are read through a
+ * FontSource, usually an IPC client of the file system server.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::psf::{self, PsfFont};
+use crate::raster::{ceil, floor, GlyphBitmap, Point, Rasterizer};
+use crate::truetype::{self, Segment, TrueTypeFont};
+
+/// Largest glyph bitmap side, in pixels
+pub const MAX_GLYPH_SIZE: u32 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    /// The source failed with this status
+    Io(i32),
+    /// Not a PSF or TrueType font, or a damaged one
+    Malformed,
+}
+
+/// Where font files come from
+pub trait FontSource {
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>, i32>;
+}
+
+/// Vertical metrics of a line in pixels; descent is below the baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_height: f32,
+}
+
+pub enum Font {
+    Psf(PsfFont),
+    TrueType(TrueTypeFont),
+}
+
+impl Font {
+    pub fn parse(data: Vec<u8>) -> Result<Self, FontError> {
+        let font = if psf::is_psf(&data) {
+            PsfFont::parse(data).map(Font::Psf)
+        } else if truetype::is_truetype(&data) {
+            TrueTypeFont::parse(data).map(Font::TrueType)
+        } else {
+            None
+        };
+        font.ok_or(FontError::Malformed)
+    }
+
+    pub fn load<S: FontSource>(source: &mut S, path: &str) -> Result<Self, FontError> {
+        Self::parse(source.read_file(path).map_err(FontError::Io)?)
+    }
+
+    pub fn is_scalable(&self) -> bool {
+        matches!(self, Font::TrueType(_))
+    }
+
+    // Whole-number magnification of a bitmap font for `size`
+    fn psf_scale(font: &PsfFont, size: u32) -> u32 {
+        (size / font.height()).max(1)
+    }
+
+    pub fn glyph_index(&self, ch: char) -> Option<u32> {
+        match self {
+            Font::Psf(font) => font.glyph_index(ch),
+            Font::TrueType(font) => font.glyph_index(ch).map(u32::from),
+        }
+    }
+
+    /// Glyph drawn for characters the font lacks
+    pub fn fallback_glyph(&self) -> u32 {
+        match self {
+            Font::Psf(font) => font.glyph_index('?').unwrap_or(0),
+            // .notdef
+            Font::TrueType(_) => 0,
+        }
+    }
+
+    pub fn line_metrics(&self, size: u32) -> LineMetrics {
+        match self {
+            Font::Psf(font) => {
+                // Cells have no baseline; the bottom of the cell is used
+                let height = (font.height() * Self::psf_scale(font, size)) as f32;
+                LineMetrics { ascent: height, descent: 0.0, line_height: height }
+            }
+            Font::TrueType(font) => {
+                let pixels = |units: i16| units as f32 * size as f32 / font.units_per_em() as f32;
+                let (ascender, descender, line_gap) = font.vertical_metrics();
+                let (ascent, descent) = (pixels(ascender), -pixels(descender));
+                LineMetrics { ascent, descent, line_height: ascent + descent + pixels(line_gap) }
+            }
+        }
+    }
+
+    pub fn advance(&self, glyph: u32, size: u32) -> f32 {
+        match self {
+            Font::Psf(font) => (font.width() * Self::psf_scale(font, size)) as f32,
+            Font::TrueType(font) => {
+                let (advance, _) = font.horizontal_metrics(glyph as u16);
+                advance as f32 * size as f32 / font.units_per_em() as f32
+            }
+        }
+    }
+
+    pub fn kerning(&self, left: u32, right: u32, size: u32) -> f32 {
+        match self {
+            Font::Psf(_) => 0.0,
+            Font::TrueType(font) => {
+                font.kerning(left as u16, right as u16) as f32 * size as f32 / font.units_per_em() as f32
+            }
+        }
+    }
+
+    /// Coverage bitmap of a glyph; None for glyphs that draw nothing
+    pub fn rasterize(&self, glyph: u32, size: u32) -> Option<GlyphBitmap> {
+        match self {
+            Font::Psf(font) => rasterize_psf(font, glyph, Self::psf_scale(font, size)),
+            Font::TrueType(font) => rasterize_outline(font, glyph as u16, size),
+        }
+    }
+}
+
+fn rasterize_psf(font: &PsfFont, glyph: u32, scale: u32) -> Option<GlyphBitmap> {
+    if glyph >= font.glyph_count() {
+        return None;
+    }
+    let (width, height) = (font.width() * scale, font.height() * scale);
+    let mut coverage = vec![0u8; (width * height) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            if font.pixel(glyph, x / scale, y / scale) {
+                coverage[(y * width + x) as usize] = 255;
+            }
+        }
+    }
+    Some(GlyphBitmap { width, height, left: 0, top: height as i32, coverage })
+}
+
+fn rasterize_outline(font: &TrueTypeFont, glyph: u16, size: u32) -> Option<GlyphBitmap> {
+    let bounds = font.bounding_box(glyph)?;
+    let scale = size as f32 / font.units_per_em() as f32;
+    let left = floor(bounds.x_min as f32 * scale);
+    let right = ceil(bounds.x_max as f32 * scale);
+    let top = ceil(bounds.y_max as f32 * scale);
+    let bottom = floor(bounds.y_min as f32 * scale);
+    let (width, height) = ((right - left) as u32, (top - bottom) as u32);
+    if width == 0 || height == 0 || width > MAX_GLYPH_SIZE || height > MAX_GLYPH_SIZE {
+        return None;
+    }
+
+    // Font units, y up, to bitmap pixels, y down
+    let to_pixels = |point: Point| Point::new(point.x * scale - left as f32, top as f32 - point.y * scale);
+    let mut rasterizer = Rasterizer::new(width, height);
+    for segment in font.outline(glyph) {
+        match segment {
+            Segment::Line(from, to) => rasterizer.line(to_pixels(from), to_pixels(to)),
+            Segment::Quad(from, control, to) => rasterizer.quad(to_pixels(from), to_pixels(control), to_pixels(to)),
+        }
+    }
+    Some(GlyphBitmap { width, height, left, top, coverage: rasterizer.coverage() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::truetype::tests::test_font;
+    use alloc::string::String;
+    use alloc::vec;
+
+    struct Files;
+
+    impl FontSource for Files {
+        fn read_file(&mut self, path: &str) -> Result<Vec<u8>, i32> {
+            match path {
+                "/fonts/test.ttf" => Ok(test_font()),
+                "/fonts/broken.ttf" => Ok(vec![0, 1, 0, 0]),
+                _ => Err(-2),
+            }
+        }
+    }
+
+    #[test]
+    fn test_scales_outline_fonts() {
+        let font = Font::load(&mut Files, "/fonts/test.ttf").unwrap();
+        assert!(font.is_scalable());
+        assert_eq!(font.line_metrics(20), LineMetrics { ascent: 16.0, descent: 4.0, line_height: 22.0 });
+        assert_eq!(font.advance(1, 20), 14.0);
+        assert_eq!(font.kerning(1, 2, 20), -1.6);
+
+        // 600x700 units at 20px: 12x14 pixels, fully covered
+        let bitmap = font.rasterize(1, 20).unwrap();
+        assert_eq!((bitmap.width, bitmap.height, bitmap.left, bitmap.top), (12, 14, 0, 14));
+        assert!(bitmap.coverage.iter().all(|&value| value == 255));
+        assert!(font.rasterize(3, 20).is_none());
+
+        assert_eq!(Font::load(&mut Files, "/fonts/broken.ttf").err(), Some(FontError::Malformed));
+        assert_eq!(Font::load(&mut Files, "/fonts/none.ttf").err(), Some(FontError::Io(-2)));
+    }
+
+    #[test]
+    fn test_magnifies_bitmap_fonts() {
+        // 8x2 font whose glyph 1 has its leftmost top pixel set
+        let mut data = vec![0x36, 0x04, 0, 2];
+        data.resize(4 + 256 * 2, 0);
+        data[4 + 2] = 0x80;
+        let font = Font::parse(data).unwrap();
+        assert_eq!(font.line_metrics(5).line_height, 4.0);
+        assert_eq!(font.advance(1, 5), 16.0);
+
+        let bitmap = font.rasterize(1, 5).unwrap();
+        assert_eq!((bitmap.width, bitmap.height, bitmap.top), (16, 4, 4));
+        let rows: Vec<String> = bitmap
+            .coverage
+            .chunks(16)
+            .map(|row| row[..4].iter().map(|&value| if value > 0 { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(rows, ["##..", "##..", "....", "...."]);
+    }
+}
@@ -0,0 +1,32 @@
+/*
+ * Orion Operating System - Text Rendering Library
+ *
+ * Fonts, glyph rasterization and text layout for the console and UI
+ * components. PSF bitmap fonts and TrueType outline fonts are loaded from
+ * the VFS, rasterized with anti-aliasing, cached in a glyph atlas, shaped
+ * and wrapped for Latin scripts, and drawn onto framebuffers.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod atlas;
+pub mod font;
+pub mod psf;
+pub mod raster;
+pub mod render;
+pub mod shape;
+pub mod truetype;
+
+pub use atlas::{AtlasEntry, AtlasStats, GlyphAtlas, GlyphKey};
+pub use font::{Font, FontError, FontSource, LineMetrics};
+pub use raster::GlyphBitmap;
+pub use render::{Canvas, FontId, PixelBuffer, TextRenderer, TextStyle};
+pub use shape::{Layout, Line, ShapedGlyph};
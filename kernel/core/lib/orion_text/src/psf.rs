@@ -0,0 +1,202 @@
+/*
+ * Orion Operating System - PC Screen Fonts
+ *
+ * PSF1 and PSF2 bitmap fonts, the format of the Linux console fonts. A
+ * glyph is a 1-bit bitmap with rows padded to whole bytes, most
+ * significant bit leftmost. The optional Unicode table maps characters to
+ * glyphs; without one, glyph N is character N.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HASTAB: u8 = 0x02;
+const PSF1_MODE_SEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_START_SEQ: u16 = 0xfffe;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_START_SEQ: u8 = 0xfe;
+
+pub fn is_psf(data: &[u8]) -> bool {
+    data.starts_with(&PSF1_MAGIC) || data.starts_with(&PSF2_MAGIC)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub struct PsfFont {
+    data: Vec<u8>,
+    glyphs_offset: usize,
+    count: u32,
+    glyph_size: usize,
+    width: u32,
+    height: u32,
+    unicode: BTreeMap<char, u32>,
+}
+
+impl PsfFont {
+    pub fn parse(data: Vec<u8>) -> Option<Self> {
+        let mut font = if data.starts_with(&PSF1_MAGIC) {
+            let mode = *data.get(2)?;
+            let height = *data.get(3)? as u32;
+            Self {
+                glyphs_offset: 4,
+                count: if mode & PSF1_MODE_512 != 0 { 512 } else { 256 },
+                glyph_size: height as usize,
+                width: 8,
+                height,
+                unicode: BTreeMap::new(),
+                data,
+            }
+        } else if data.starts_with(&PSF2_MAGIC) {
+            Self {
+                glyphs_offset: read_u32(&data, 8)? as usize,
+                count: read_u32(&data, 16)?,
+                glyph_size: read_u32(&data, 20)? as usize,
+                height: read_u32(&data, 24)?,
+                width: read_u32(&data, 28)?,
+                unicode: BTreeMap::new(),
+                data,
+            }
+        } else {
+            return None;
+        };
+
+        let table = font.glyphs_offset.checked_add(font.count as usize * font.glyph_size)?;
+        if font.width == 0
+            || font.height == 0
+            || font.glyph_size < font.width.div_ceil(8) as usize * font.height as usize
+            || table > font.data.len()
+        {
+            return None;
+        }
+        if font.data.starts_with(&PSF1_MAGIC) {
+            if font.data[2] & (PSF1_MODE_HASTAB | PSF1_MODE_SEQ) != 0 {
+                font.parse_psf1_table(table);
+            }
+        } else if read_u32(&font.data, 12)? & PSF2_HAS_UNICODE_TABLE != 0 {
+            font.parse_psf2_table(table);
+        }
+        Some(font)
+    }
+
+    // Per glyph: UCS-2 values, sequences introduced by 0xfffe, 0xffff ends
+    fn parse_psf1_table(&mut self, table: usize) {
+        let mut glyph = 0;
+        let mut in_sequence = false;
+        for pair in self.data[table..].chunks_exact(2) {
+            match u16::from_le_bytes([pair[0], pair[1]]) {
+                PSF1_SEPARATOR => {
+                    glyph += 1;
+                    in_sequence = false;
+                }
+                PSF1_START_SEQ => in_sequence = true,
+                value if !in_sequence => {
+                    if let Some(ch) = char::from_u32(value as u32) {
+                        self.unicode.entry(ch).or_insert(glyph);
+                    }
+                }
+                _ => {}
+            }
+            if glyph >= self.count {
+                break;
+            }
+        }
+    }
+
+    // Per glyph: UTF-8 characters, sequences introduced by 0xfe, 0xff ends
+    fn parse_psf2_table(&mut self, table: usize) {
+        let entries = self.data[table..].split(|&byte| byte == PSF2_SEPARATOR);
+        for (glyph, entry) in (0..self.count).zip(entries) {
+            let singles = entry.split(|&byte| byte == PSF2_START_SEQ).next().unwrap_or(&[]);
+            if let Ok(text) = core::str::from_utf8(singles) {
+                for ch in text.chars() {
+                    self.unicode.entry(ch).or_insert(glyph);
+                }
+            }
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn glyph_count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn glyph_index(&self, ch: char) -> Option<u32> {
+        if self.unicode.is_empty() {
+            return Some(ch as u32).filter(|&glyph| glyph < self.count);
+        }
+        self.unicode.get(&ch).copied()
+    }
+
+    /// Whether pixel (x, y) of `glyph` is set
+    pub fn pixel(&self, glyph: u32, x: u32, y: u32) -> bool {
+        if glyph >= self.count || x >= self.width || y >= self.height {
+            return false;
+        }
+        let row = self.glyphs_offset + glyph as usize * self.glyph_size + (y * self.width.div_ceil(8)) as usize;
+        self.data[row + x as usize / 8] & (0x80 >> (x % 8)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_parses_psf1_with_table() {
+        // Two 8x2 glyphs, the second one mapped from 'A' and 'Ä'
+        let mut data = vec![0x36, 0x04, PSF1_MODE_HASTAB, 2];
+        data.resize(4 + 256 * 2, 0);
+        data[4 + 2] = 0b1000_0001;
+        for value in [0xffffu16, 0x41, 0xc4, 0xffff] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let font = PsfFont::parse(data).unwrap();
+        assert_eq!((font.width(), font.height(), font.glyph_count()), (8, 2, 256));
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('Ä'), Some(1));
+        assert_eq!(font.glyph_index('B'), None);
+        assert!(font.pixel(1, 0, 0) && font.pixel(1, 7, 0) && !font.pixel(1, 1, 0) && !font.pixel(1, 0, 1));
+    }
+
+    #[test]
+    fn test_parses_psf2() {
+        // Three 10x2 glyphs of two bytes per row, no table
+        let mut data = PSF2_MAGIC.to_vec();
+        for value in [0u32, 32, 0, 3, 4, 2, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&[0x00, 0x40, 0x00, 0x00]);
+        data.extend_from_slice(&[0; 4]);
+        let font = PsfFont::parse(data.clone()).unwrap();
+        assert_eq!(font.glyph_index('\u{1}'), Some(1));
+        assert_eq!(font.glyph_index('\u{3}'), None);
+        assert!(font.pixel(1, 9, 0) && !font.pixel(1, 8, 0));
+
+        data.truncate(data.len() - 1);
+        assert!(PsfFont::parse(data).is_none());
+    }
+}
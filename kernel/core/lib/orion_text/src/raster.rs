@@ -0,0 +1,243 @@
+/*
+ * Orion Operating System - Outline Rasterizer
+ *
+ * Anti-aliased scan conversion of glyph outlines. Each line segment adds
+ * its signed area contribution to an accumulation buffer, one cell per
+ * pixel; a running sum along every row then gives the exact coverage of
+ * each pixel under the non-zero rule for non-overlapping contours.
+ * Quadratic curves are flattened into lines first. Only basic float
+ * arithmetic is used, as no_std has no libm.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Largest distance, in pixels, between a curve and its flattening
+const FLATTEN_TOLERANCE: f32 = 0.1;
+
+/// Most lines a single curve is flattened into
+const MAX_CURVE_LINES: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    fn lerp(self, other: Point, t: f32) -> Point {
+        Point::new(self.x + (other.x - self.x) * t, self.y + (other.y - self.y) * t)
+    }
+}
+
+pub fn floor(value: f32) -> i32 {
+    let truncated = value as i32;
+    if (truncated as f32) > value {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
+
+pub fn ceil(value: f32) -> i32 {
+    -floor(-value)
+}
+
+pub fn round(value: f32) -> i32 {
+    floor(value + 0.5)
+}
+
+fn abs(value: f32) -> f32 {
+    if value < 0.0 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Smallest n with n * n >= value
+fn ceil_sqrt(value: f32) -> u32 {
+    let mut n = 1;
+    while ((n * n) as f32) < value && n < MAX_CURVE_LINES {
+        n += 1;
+    }
+    n
+}
+
+/// 8-bit coverage of a glyph; `left` and `top` place its top-left corner
+/// relative to the pen position on the baseline (top grows upwards)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    pub left: i32,
+    pub top: i32,
+    pub coverage: Vec<u8>,
+}
+
+pub struct Rasterizer {
+    width: usize,
+    height: usize,
+    accumulation: Vec<f32>,
+}
+
+impl Rasterizer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self { width, height, accumulation: vec![0.0; width * height] }
+    }
+
+    /// Add a line in pixel coordinates, y growing downwards
+    pub fn line(&mut self, from: Point, to: Point) {
+        if from.y == to.y || self.width == 0 {
+            return;
+        }
+        let (direction, top, bottom) = if from.y < to.y { (1.0, from, to) } else { (-1.0, to, from) };
+        let slope = (bottom.x - top.x) / (bottom.y - top.y);
+        let max_x = self.width as f32;
+        let mut x = top.x;
+        if top.y < 0.0 {
+            x -= top.y * slope;
+        }
+        let first_row = floor(top.y).max(0) as usize;
+        let last_row = (ceil(bottom.y).max(0) as usize).min(self.height);
+
+        for row in first_row..last_row {
+            let row_start = row * self.width;
+            let dy = bottom.y.min(row as f32 + 1.0) - top.y.max(row as f32);
+            let next_x = x + slope * dy;
+            let delta = dy * direction;
+            // Clamp so edges outside the bitmap still close their spans
+            let (x0, x1) = if x < next_x { (x, next_x) } else { (next_x, x) };
+            let (x0, x1) = (x0.clamp(0.0, max_x), x1.clamp(0.0, max_x));
+            let x0_floor = floor(x0);
+            let x1_ceil = ceil(x1);
+            // Contributions right of the bitmap cannot affect it
+            let width = self.width;
+            let accumulation = &mut self.accumulation;
+            let mut add = |column: i32, value: f32| {
+                if (column as usize) < width {
+                    accumulation[row_start + column as usize] += value;
+                }
+            };
+
+            if x1_ceil <= x0_floor + 1 {
+                // The segment stays within one pixel column
+                let middle = (x0 + x1) / 2.0 - x0_floor as f32;
+                add(x0_floor, delta - delta * middle);
+                add(x0_floor + 1, delta * middle);
+            } else {
+                let inverse = 1.0 / (x1 - x0);
+                let x0_fraction = x0 - x0_floor as f32;
+                let first = 0.5 * inverse * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_ceil as f32 + 1.0;
+                let last = 0.5 * inverse * x1_fraction * x1_fraction;
+                add(x0_floor, delta * first);
+                if x1_ceil == x0_floor + 2 {
+                    add(x0_floor + 1, delta * (1.0 - first - last));
+                } else {
+                    let second = inverse * (1.5 - x0_fraction);
+                    add(x0_floor + 1, delta * (second - first));
+                    for column in x0_floor + 2..x1_ceil - 1 {
+                        add(column, delta * inverse);
+                    }
+                    let before_last = second + (x1_ceil - x0_floor - 3) as f32 * inverse;
+                    add(x1_ceil - 1, delta * (1.0 - before_last - last));
+                }
+                add(x1_ceil, delta * last);
+            }
+            x = next_x;
+        }
+    }
+
+    /// Add a quadratic curve, flattened to within FLATTEN_TOLERANCE
+    pub fn quad(&mut self, from: Point, control: Point, to: Point) {
+        // The distance between the curve and its chord is at most a quarter
+        // of |from - 2 control + to|, and shrinks with the square of the
+        // number of pieces
+        let deviation = abs(from.x - 2.0 * control.x + to.x) + abs(from.y - 2.0 * control.y + to.y);
+        let lines = ceil_sqrt(deviation / (4.0 * FLATTEN_TOLERANCE));
+        let mut previous = from;
+        for step in 1..=lines {
+            let t = step as f32 / lines as f32;
+            let next = from.lerp(control, t).lerp(control.lerp(to, t), t);
+            self.line(previous, next);
+            previous = next;
+        }
+    }
+
+    /// Coverage of every pixel, row by row
+    pub fn coverage(&self) -> Vec<u8> {
+        let mut sum = 0.0;
+        self.accumulation
+            .iter()
+            .enumerate()
+            .map(|(index, &cell)| {
+                if index % self.width == 0 {
+                    sum = 0.0;
+                }
+                sum += cell;
+                (abs(sum).min(1.0) * 255.0 + 0.5) as u8
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(rasterizer: &mut Rasterizer, points: &[Point]) {
+        for (index, &point) in points.iter().enumerate() {
+            rasterizer.line(point, points[(index + 1) % points.len()]);
+        }
+    }
+
+    #[test]
+    fn test_covers_partial_pixels() {
+        // Square from (0.5, 0.5) to (2.5, 2.5) on a 3x3 bitmap
+        let mut rasterizer = Rasterizer::new(3, 3);
+        let corners = [Point::new(0.5, 0.5), Point::new(2.5, 0.5), Point::new(2.5, 2.5), Point::new(0.5, 2.5)];
+        fill(&mut rasterizer, &corners);
+        assert_eq!(rasterizer.coverage(), [64, 128, 64, 128, 255, 128, 64, 128, 64]);
+
+        // Same square wound the other way
+        let mut reversed = Rasterizer::new(3, 3);
+        let mut corners = corners;
+        corners.reverse();
+        fill(&mut reversed, &corners);
+        assert_eq!(reversed.coverage(), rasterizer.coverage());
+
+        // Diagonal: half of every pixel it crosses
+        let mut triangle = Rasterizer::new(2, 2);
+        fill(&mut triangle, &[Point::new(0.0, 0.0), Point::new(2.0, 2.0), Point::new(0.0, 2.0)]);
+        assert_eq!(triangle.coverage(), [128, 0, 255, 128]);
+    }
+
+    #[test]
+    fn test_flattens_curves() {
+        let mut rasterizer = Rasterizer::new(8, 8);
+        let (a, b, c) = (Point::new(0.0, 8.0), Point::new(4.0, 0.0), Point::new(8.0, 8.0));
+        rasterizer.quad(a, b, c);
+        rasterizer.line(c, a);
+        let coverage = rasterizer.coverage();
+        // Symmetric, full at the bottom centre, empty in the top corners
+        for row in coverage.chunks(8) {
+            assert!(row.iter().zip(row.iter().rev()).all(|(left, right)| left.abs_diff(*right) <= 1));
+        }
+        assert_eq!(coverage[7 * 8 + 4], 255);
+        assert_eq!(coverage[0], 0);
+        assert_eq!(coverage[7], 0);
+        assert_eq!((floor(-0.5), ceil(-0.5), round(2.5), ceil(3.0)), (-1, 0, 3, 3));
+    }
+}
@@ -0,0 +1,243 @@
+/*
+ * Orion Operating System - Text Rendering
+ *
+ * Draws laid out text onto a Canvas through the glyph atlas. A Canvas is
+ * anything with addressable pixels: PixelBuffer wraps a mapped xRGB8888
+ * framebuffer and blends anti-aliased edges, while a GraphicsDriver is
+ * adapted by forwarding size() to get_framebuffer_info() and set_pixel()
+ * to its own set_pixel(). Drivers cannot read pixels back, so the
+ * default blend_pixel() draws a pixel when it is at least half covered.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::atlas::{GlyphAtlas, GlyphKey};
+use crate::font::{Font, FontError, FontSource};
+use crate::raster::{ceil, round};
+use crate::shape::{self, Layout};
+
+/// Side of the default atlas texture
+pub const DEFAULT_ATLAS_SIZE: u32 = 512;
+
+pub trait Canvas {
+    /// Width and height in pixels
+    fn size(&self) -> (u32, u32);
+
+    /// Set a pixel to an xRGB8888 color
+    fn set_pixel(&mut self, x: u32, y: u32, color: u32);
+
+    /// Draw `color` over a pixel with coverage `alpha`
+    fn blend_pixel(&mut self, x: u32, y: u32, color: u32, alpha: u8) {
+        if alpha >= 128 {
+            self.set_pixel(x, y, color);
+        }
+    }
+}
+
+/// Mapped xRGB8888 pixels, `stride` pixels per row
+pub struct PixelBuffer<'a> {
+    pixels: &'a mut [u32],
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+impl<'a> PixelBuffer<'a> {
+    pub fn new(pixels: &'a mut [u32], width: u32, height: u32, stride: u32) -> Option<Self> {
+        let needed = if height == 0 { 0 } else { (height as usize - 1) * stride as usize + width as usize };
+        (stride >= width && pixels.len() >= needed).then_some(Self { pixels, width, height, stride })
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> u32 {
+        self.pixels[(y * self.stride + x) as usize]
+    }
+}
+
+impl Canvas for PixelBuffer<'_> {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: u32) {
+        self.pixels[(y * self.stride + x) as usize] = color;
+    }
+
+    fn blend_pixel(&mut self, x: u32, y: u32, color: u32, alpha: u8) {
+        let pixel = &mut self.pixels[(y * self.stride + x) as usize];
+        let (alpha, inverse) = (alpha as u32, 255 - alpha as u32);
+        let mut blended = 0;
+        for shift in [0, 8, 16] {
+            let source = (color >> shift) & 0xff;
+            let target = (*pixel >> shift) & 0xff;
+            blended |= ((source * alpha + target * inverse + 127) / 255) << shift;
+        }
+        *pixel = blended;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStyle {
+    pub font: FontId,
+    /// Pixels per em
+    pub size: u32,
+    /// xRGB8888
+    pub color: u32,
+}
+
+pub struct TextRenderer {
+    fonts: Vec<Font>,
+    atlas: GlyphAtlas,
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self {
+        Self::new(DEFAULT_ATLAS_SIZE)
+    }
+}
+
+impl TextRenderer {
+    pub fn new(atlas_size: u32) -> Self {
+        Self { fonts: Vec::new(), atlas: GlyphAtlas::new(atlas_size, atlas_size) }
+    }
+
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() as u32 - 1)
+    }
+
+    pub fn load_font<S: FontSource>(&mut self, source: &mut S, path: &str) -> Result<FontId, FontError> {
+        Ok(self.add_font(Font::load(source, path)?))
+    }
+
+    pub fn font(&self, id: FontId) -> Option<&Font> {
+        self.fonts.get(id.0 as usize)
+    }
+
+    pub fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
+    /// Lay out text, wrapping at `max_width` when given
+    pub fn layout(&self, style: &TextStyle, text: &str, max_width: Option<u32>) -> Option<Layout> {
+        let font = self.font(style.font)?;
+        Some(shape::layout(font, text, style.size, max_width.map(|width| width as f32)))
+    }
+
+    /// Width and height, in whole pixels, of text drawn with `style`
+    pub fn measure(&self, style: &TextStyle, text: &str, max_width: Option<u32>) -> Option<(u32, u32)> {
+        let layout = self.layout(style, text, max_width)?;
+        Some((ceil(layout.width) as u32, ceil(layout.height) as u32))
+    }
+
+    /// Draw a string with the top of its first line at (x, y); newlines
+    /// start new lines. Returns the extent drawn, as measure() does.
+    pub fn draw_string<C: Canvas>(
+        &mut self,
+        canvas: &mut C,
+        style: &TextStyle,
+        text: &str,
+        x: i32,
+        y: i32,
+    ) -> Option<(u32, u32)> {
+        self.draw_text(canvas, style, text, x, y, None)
+    }
+
+    /// draw_string(), wrapping lines at `max_width`
+    pub fn draw_text<C: Canvas>(
+        &mut self,
+        canvas: &mut C,
+        style: &TextStyle,
+        text: &str,
+        x: i32,
+        y: i32,
+        max_width: Option<u32>,
+    ) -> Option<(u32, u32)> {
+        let layout = self.layout(style, text, max_width)?;
+        let font = &self.fonts[style.font.0 as usize];
+        let (canvas_width, canvas_height) = canvas.size();
+
+        for line in &layout.lines {
+            let baseline = y + round(line.baseline);
+            for glyph in &line.glyphs {
+                let key = GlyphKey { font: style.font.0, glyph: glyph.glyph, size: style.size };
+                let Some(entry) = self.atlas.get_or_insert(key, || font.rasterize(glyph.glyph, style.size)) else {
+                    continue;
+                };
+                let left = x + round(glyph.x) + entry.left;
+                let top = baseline - entry.top;
+                for row in 0..entry.height {
+                    let target_y = top + row as i32;
+                    if target_y < 0 || target_y >= canvas_height as i32 {
+                        continue;
+                    }
+                    for column in 0..entry.width {
+                        let target_x = left + column as i32;
+                        let alpha = self.atlas.coverage(&entry, column, row);
+                        if alpha > 0 && target_x >= 0 && target_x < canvas_width as i32 {
+                            canvas.blend_pixel(target_x as u32, target_y as u32, style.color, alpha);
+                        }
+                    }
+                }
+            }
+        }
+        Some((ceil(layout.width) as u32, ceil(layout.height) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::truetype::tests::test_font;
+    use alloc::vec;
+
+    #[test]
+    fn test_draws_into_pixel_buffers() {
+        let mut renderer = TextRenderer::new(64);
+        let font = renderer.add_font(Font::parse(test_font()).unwrap());
+        let style = TextStyle { font, size: 20, color: 0x00ff_8000 };
+        let mut pixels = vec![0u32; 32 * 24];
+        let mut canvas = PixelBuffer::new(&mut pixels, 30, 24, 32).unwrap();
+
+        // 'A' is a 12x14 box standing on the baseline, 16 pixels down
+        assert_eq!(renderer.draw_string(&mut canvas, &style, "A", 2, 0), Some((14, 22)));
+        assert_eq!((canvas.pixel(2, 2), canvas.pixel(13, 15)), (0x00ff_8000, 0x00ff_8000));
+        assert_eq!((canvas.pixel(1, 2), canvas.pixel(2, 1), canvas.pixel(14, 15), canvas.pixel(2, 16)), (0, 0, 0, 0));
+
+        // Half covered pixels are blended, clipped ones skipped
+        canvas.blend_pixel(0, 0, 0x00ff_ffff, 128);
+        assert_eq!(canvas.pixel(0, 0), 0x0080_8080);
+        renderer.draw_string(&mut canvas, &style, "AA", 20, 20).unwrap();
+        assert_eq!(renderer.atlas().stats().hits, 2);
+        assert_eq!(renderer.measure(&style, "AA AA", Some(40)), Some((28, 44)));
+        assert_eq!(renderer.measure(&TextStyle { font: FontId(1), ..style }, "A", None), None);
+    }
+
+    #[test]
+    fn test_thresholds_on_write_only_canvases() {
+        struct Driver(Vec<(u32, u32)>);
+
+        impl Canvas for Driver {
+            fn size(&self) -> (u32, u32) {
+                (4, 4)
+            }
+
+            fn set_pixel(&mut self, x: u32, y: u32, _color: u32) {
+                self.0.push((x, y));
+            }
+        }
+
+        let mut driver = Driver(Vec::new());
+        driver.blend_pixel(0, 0, 1, 127);
+        driver.blend_pixel(1, 0, 1, 128);
+        assert_eq!(driver.0, [(1, 0)]);
+    }
+}
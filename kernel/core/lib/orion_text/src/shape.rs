@@ -0,0 +1,186 @@
+/*
+ * Orion Operating System - Text Shaping and Layout
+ *
+ * Shaping for Latin scripts: characters map one to one onto glyphs, pairs
+ * are kerned, combining diacritics (U+0300 to U+036F) are centred over
+ * the preceding base glyph without advancing the pen, and tabs advance to
+ * the next multiple of TAB_WIDTH spaces. Layout breaks shaped text into
+ * lines at newlines and, given a width, at the last space that fits (or
+ * anywhere within a word too long for a line).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::font::Font;
+
+/// Tab stops, in spaces
+pub const TAB_WIDTH: u32 = 8;
+
+/// Glyph placed relative to the start of its line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph: u32,
+    /// Pen position on the baseline
+    pub x: f32,
+    pub advance: f32,
+    /// Byte offset of the character in the text
+    pub cluster: usize,
+}
+
+fn is_combining_mark(ch: char) -> bool {
+    matches!(ch, '\u{0300}'..='\u{036f}')
+}
+
+/// Shape a single line; newlines are ignored
+pub fn shape(font: &Font, text: &str, size: u32) -> Vec<ShapedGlyph> {
+    let space = font.glyph_index(' ').map_or(size as f32 / 2.0, |glyph| font.advance(glyph, size));
+    let tab = space * TAB_WIDTH as f32;
+    let mut glyphs: Vec<ShapedGlyph> = Vec::new();
+    let mut pen = 0.0;
+    let mut previous: Option<u32> = None;
+    let mut base: Option<usize> = None;
+
+    for (cluster, ch) in text.char_indices() {
+        match ch {
+            '\n' | '\r' => continue,
+            '\t' => {
+                pen = (pen / tab + 1.0) as u32 as f32 * tab;
+                previous = None;
+                base = None;
+                continue;
+            }
+            _ => {}
+        }
+        let glyph = font.glyph_index(ch).unwrap_or_else(|| font.fallback_glyph());
+        let advance = font.advance(glyph, size);
+
+        if is_combining_mark(ch) {
+            if let Some(base) = base.map(|index| glyphs[index]) {
+                let x = base.x + (base.advance - advance) / 2.0;
+                glyphs.push(ShapedGlyph { glyph, x, advance: 0.0, cluster });
+                continue;
+            }
+        }
+        if let Some(left) = previous {
+            pen += font.kerning(left, glyph, size);
+        }
+        base = Some(glyphs.len());
+        glyphs.push(ShapedGlyph { glyph, x: pen, advance, cluster });
+        pen += advance;
+        previous = Some(glyph);
+    }
+    glyphs
+}
+
+/// Width of shaped glyphs, from the first pen position to the last advance
+pub fn width(glyphs: &[ShapedGlyph]) -> f32 {
+    glyphs.iter().map(|glyph| glyph.x + glyph.advance).fold(0.0, f32::max)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub width: f32,
+    /// Baseline, from the top of the layout
+    pub baseline: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub lines: Vec<Line>,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Break `text` into lines no wider than `max_width` (when given)
+pub fn layout(font: &Font, text: &str, size: u32, max_width: Option<f32>) -> Layout {
+    let metrics = font.line_metrics(size);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for paragraph in text.split('\n') {
+        let glyphs = shape(font, paragraph, size);
+        let mut glyphs: Vec<ShapedGlyph> =
+            glyphs.into_iter().map(|glyph| ShapedGlyph { cluster: glyph.cluster + offset, ..glyph }).collect();
+        offset += paragraph.len() + 1;
+
+        loop {
+            let split = max_width.and_then(|max| break_point(&glyphs, text, max));
+            let Some((end, next)) = split else {
+                lines.push(glyphs);
+                break;
+            };
+            let rest = glyphs.split_off(next);
+            glyphs.truncate(end);
+            lines.push(glyphs);
+            // The next line starts at the pen position of its first glyph
+            let shift = rest.first().map_or(0.0, |glyph| glyph.x);
+            glyphs = rest.into_iter().map(|glyph| ShapedGlyph { x: glyph.x - shift, ..glyph }).collect();
+        }
+    }
+
+    let mut baseline = metrics.ascent;
+    let mut layout = Layout { lines: Vec::with_capacity(lines.len()), width: 0.0, height: 0.0 };
+    for glyphs in lines {
+        let line_width = width(&glyphs);
+        layout.width = layout.width.max(line_width);
+        layout.lines.push(Line { glyphs, width: line_width, baseline });
+        baseline += metrics.line_height;
+    }
+    layout.height = layout.lines.len() as f32 * metrics.line_height;
+    layout
+}
+
+/// Where to end a line that is too wide: glyphs before `end` stay, the
+/// next line starts at `next` (skipping the space broken at)
+fn break_point(glyphs: &[ShapedGlyph], text: &str, max_width: f32) -> Option<(usize, usize)> {
+    let overflow = glyphs.iter().position(|glyph| glyph.advance > 0.0 && glyph.x + glyph.advance > max_width)?;
+    let is_space = |glyph: &ShapedGlyph| text[glyph.cluster..].starts_with(' ');
+    match glyphs[..=overflow].iter().rposition(is_space) {
+        Some(space) if space > 0 => Some((space, space + 1)),
+        // A word longer than the line is cut, keeping marks on their base
+        _ => {
+            let cut = match overflow {
+                0 => {
+                    glyphs.iter().skip(1).position(|glyph| glyph.advance > 0.0).map_or(glyphs.len(), |index| index + 1)
+                }
+                _ => overflow,
+            };
+            (cut < glyphs.len()).then_some((cut, cut))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::truetype::tests::test_font;
+
+    #[test]
+    fn test_kerns_and_places_marks() {
+        // Glyph advances at 20px: 'A' and 'V' 14, ' ' 5, missing 10
+        let font = Font::parse(test_font()).unwrap();
+        let glyphs = shape(&font, "AV\u{0301}\tB", 20);
+        let placed: Vec<(u32, f32, usize)> = glyphs.iter().map(|glyph| (glyph.glyph, glyph.x, glyph.cluster)).collect();
+        assert_eq!(placed, [(1, 0.0, 0), (2, 12.4, 1), (0, 14.4, 2), (0, 40.0, 5)]);
+        assert_eq!(width(&glyphs), 50.0);
+    }
+
+    #[test]
+    fn test_wraps_lines() {
+        let font = Font::parse(test_font()).unwrap();
+        let layout = layout(&font, "AA AA\nAAAA", 20, Some(40.0));
+        let lines: Vec<(usize, f32, f32)> =
+            layout.lines.iter().map(|line| (line.glyphs.len(), line.width, line.baseline)).collect();
+        // "AA" / "AA" / "AA" / "AA": a space is broken at, words are cut
+        assert_eq!(lines, [(2, 28.0, 16.0), (2, 28.0, 38.0), (2, 28.0, 60.0), (2, 28.0, 82.0)]);
+        assert_eq!(layout.lines[1].glyphs[0].cluster, 3);
+        assert_eq!(layout.lines[3].glyphs[0].cluster, 8);
+        assert_eq!((layout.width, layout.height), (28.0, 88.0));
+    }
+}
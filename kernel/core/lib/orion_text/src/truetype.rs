@@ -0,0 +1,604 @@
+/*
+ * Orion Operating System - TrueType Fonts
+ *
+ * Just enough of the TrueType format to draw text: the character map
+ * (formats 4 and 12), horizontal metrics, the kern table (format 0) and
+ * glyph outlines from glyf/loca, composite glyphs included. Hinting
+ * instructions are ignored; outlines are scaled and anti-aliased by the
+ * rasterizer instead. CFF-flavoured OpenType fonts are not supported.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::raster::Point;
+
+/// Composite glyphs nested deeper than this are not drawn
+const MAX_COMPOSITE_DEPTH: u32 = 8;
+
+// Simple glyph flags
+const FLAG_ON_CURVE: u8 = 0x01;
+const FLAG_X_SHORT: u8 = 0x02;
+const FLAG_Y_SHORT: u8 = 0x04;
+const FLAG_REPEAT: u8 = 0x08;
+const FLAG_X_SAME_OR_POSITIVE: u8 = 0x10;
+const FLAG_Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+// Composite glyph flags
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_f2dot14(data: &[u8], offset: usize) -> Option<f32> {
+    read_i16(data, offset).map(|value| value as f32 / 16384.0)
+}
+
+pub fn is_truetype(data: &[u8]) -> bool {
+    matches!(read_u32(data, 0), Some(0x0001_0000) | Some(0x7472_7565)) // 'true'
+}
+
+/// Quadratic outline segment in font units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line(Point, Point),
+    Quad(Point, Point, Point),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub x_min: i16,
+    pub y_min: i16,
+    pub x_max: i16,
+    pub y_max: i16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Table {
+    offset: usize,
+    length: usize,
+}
+
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    units_per_em: u16,
+    long_loca: bool,
+    glyph_count: u16,
+    ascender: i16,
+    descender: i16,
+    line_gap: i16,
+    metrics_count: u16,
+    /// Offset of the chosen cmap subtable
+    cmap: usize,
+    hmtx: Table,
+    loca: Table,
+    glyf: Table,
+    /// Offset and pair count of the format 0 kern subtable
+    kern: Option<(usize, usize)>,
+}
+
+impl TrueTypeFont {
+    pub fn parse(data: Vec<u8>) -> Option<Self> {
+        if !is_truetype(&data) {
+            return None;
+        }
+        let find = |tag: &[u8; 4]| -> Option<Table> {
+            let count = read_u16(&data, 4)? as usize;
+            (0..count).map(|index| 12 + index * 16).find_map(|record| {
+                if data.get(record..record + 4)? != tag {
+                    return None;
+                }
+                let table = Table {
+                    offset: read_u32(&data, record + 8)? as usize,
+                    length: read_u32(&data, record + 12)? as usize,
+                };
+                (table.offset.checked_add(table.length)? <= data.len()).then_some(table)
+            })
+        };
+        let head = find(b"head")?;
+        let maxp = find(b"maxp")?;
+        let hhea = find(b"hhea")?;
+        let cmap = find(b"cmap")?;
+        let hmtx = find(b"hmtx")?;
+        let loca = find(b"loca")?;
+        let glyf = find(b"glyf")?;
+        let kern = find(b"kern").and_then(|kern| kern_subtable(&data, kern));
+
+        let units_per_em = read_u16(&data, head.offset + 18)?;
+        let metrics_count = read_u16(&data, hhea.offset + 34)?;
+        if units_per_em == 0 || metrics_count == 0 || hmtx.length < metrics_count as usize * 4 {
+            return None;
+        }
+        Some(Self {
+            units_per_em,
+            long_loca: read_i16(&data, head.offset + 50)? != 0,
+            glyph_count: read_u16(&data, maxp.offset + 4)?,
+            ascender: read_i16(&data, hhea.offset + 4)?,
+            descender: read_i16(&data, hhea.offset + 6)?,
+            line_gap: read_i16(&data, hhea.offset + 8)?,
+            metrics_count,
+            cmap: cmap_subtable(&data, cmap)?,
+            hmtx,
+            loca,
+            glyf,
+            kern,
+            data,
+        })
+    }
+
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_count
+    }
+
+    /// Ascender, descender (negative) and line gap in font units
+    pub fn vertical_metrics(&self) -> (i16, i16, i16) {
+        (self.ascender, self.descender, self.line_gap)
+    }
+
+    pub fn glyph_index(&self, ch: char) -> Option<u16> {
+        let code = ch as u32;
+        let data = &self.data;
+        let table = self.cmap;
+        let glyph = match read_u16(data, table)? {
+            4 => {
+                if code > 0xffff {
+                    return None;
+                }
+                let segments = read_u16(data, table + 6)? as usize / 2;
+                let ends = table + 14;
+                let starts = ends + segments * 2 + 2;
+                let deltas = starts + segments * 2;
+                let range_offsets = deltas + segments * 2;
+                let segment = (0..segments)
+                    .find(|&index| read_u16(data, ends + index * 2).is_some_and(|end| code <= end as u32))?;
+                let start = read_u16(data, starts + segment * 2)? as u32;
+                if code < start {
+                    return None;
+                }
+                let delta = read_u16(data, deltas + segment * 2)?;
+                let range_offset = read_u16(data, range_offsets + segment * 2)? as usize;
+                if range_offset == 0 {
+                    (code as u16).wrapping_add(delta)
+                } else {
+                    // Offset relative to the idRangeOffset entry itself
+                    let address = range_offsets + segment * 2 + range_offset + (code - start) as usize * 2;
+                    match read_u16(data, address)? {
+                        0 => 0,
+                        glyph => glyph.wrapping_add(delta),
+                    }
+                }
+            }
+            12 => {
+                let groups = read_u32(data, table + 12)? as usize;
+                (0..groups).find_map(|index| {
+                    let group = table + 16 + index * 12;
+                    let start = read_u32(data, group)?;
+                    let end = read_u32(data, group + 4)?;
+                    (start..=end)
+                        .contains(&code)
+                        .then(|| read_u32(data, group + 8).map(|first| first + code - start))?
+                })? as u16
+            }
+            _ => return None,
+        };
+        Some(glyph).filter(|&glyph| glyph != 0 && glyph < self.glyph_count)
+    }
+
+    /// Advance width and left side bearing in font units
+    pub fn horizontal_metrics(&self, glyph: u16) -> (u16, i16) {
+        let table = self.hmtx.offset;
+        let long = glyph.min(self.metrics_count - 1) as usize;
+        let advance = read_u16(&self.data, table + long * 4).unwrap_or(0);
+        let bearing = if glyph < self.metrics_count {
+            read_i16(&self.data, table + long * 4 + 2)
+        } else {
+            let index = self.metrics_count as usize * 4 + (glyph - self.metrics_count) as usize * 2;
+            if index + 2 <= self.hmtx.length {
+                read_i16(&self.data, table + index)
+            } else {
+                None
+            }
+        };
+        (advance, bearing.unwrap_or(0))
+    }
+
+    /// Kerning adjustment between two glyphs in font units
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let Some((pairs, count)) = self.kern else {
+            return 0;
+        };
+        let key = (left as u32) << 16 | right as u32;
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let pair = pairs + middle * 6;
+            match read_u32(&self.data, pair).map(|found| found.cmp(&key)) {
+                Some(core::cmp::Ordering::Less) => low = middle + 1,
+                Some(core::cmp::Ordering::Greater) => high = middle,
+                Some(core::cmp::Ordering::Equal) => return read_i16(&self.data, pair + 4).unwrap_or(0),
+                None => return 0,
+            }
+        }
+        0
+    }
+
+    fn glyph_data(&self, glyph: u16) -> Option<&[u8]> {
+        if glyph >= self.glyph_count {
+            return None;
+        }
+        let index = glyph as usize;
+        let (start, end) = if self.long_loca {
+            let offset = self.loca.offset + index * 4;
+            (read_u32(&self.data, offset)? as usize, read_u32(&self.data, offset + 4)? as usize)
+        } else {
+            let offset = self.loca.offset + index * 2;
+            (read_u16(&self.data, offset)? as usize * 2, read_u16(&self.data, offset + 2)? as usize * 2)
+        };
+        if start >= end || end > self.glyf.length {
+            return None;
+        }
+        self.data.get(self.glyf.offset + start..self.glyf.offset + end)
+    }
+
+    /// Bounding box of a glyph; None for glyphs without outline (space)
+    pub fn bounding_box(&self, glyph: u16) -> Option<BoundingBox> {
+        let data = self.glyph_data(glyph)?;
+        Some(BoundingBox {
+            x_min: read_i16(data, 2)?,
+            y_min: read_i16(data, 4)?,
+            x_max: read_i16(data, 6)?,
+            y_max: read_i16(data, 8)?,
+        })
+    }
+
+    /// Outline of a glyph in font units
+    pub fn outline(&self, glyph: u16) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        self.append_outline(glyph, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 0, &mut segments);
+        segments
+    }
+
+    // `transform` is [xx, yx, xy, yy, dx, dy]
+    fn append_outline(&self, glyph: u16, transform: [f32; 6], depth: u32, out: &mut Vec<Segment>) -> Option<()> {
+        let data = self.glyph_data(glyph)?;
+        let contours = read_i16(data, 0)?;
+        if contours >= 0 {
+            append_simple(data, contours as usize, &transform, out)
+        } else if depth < MAX_COMPOSITE_DEPTH {
+            self.append_composite(data, transform, depth, out)
+        } else {
+            None
+        }
+    }
+
+    fn append_composite(&self, data: &[u8], parent: [f32; 6], depth: u32, out: &mut Vec<Segment>) -> Option<()> {
+        let mut offset = 10;
+        loop {
+            let flags = read_u16(data, offset)?;
+            let component = read_u16(data, offset + 2)?;
+            offset += 4;
+            let (dx, dy) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                offset += 4;
+                (read_i16(data, offset - 4)? as f32, read_i16(data, offset - 2)? as f32)
+            } else {
+                offset += 2;
+                (*data.get(offset - 2)? as i8 as f32, *data.get(offset - 1)? as i8 as f32)
+            };
+            // Point-matched placement is rare and not supported
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 { (dx, dy) } else { (0.0, 0.0) };
+            let (mut xx, mut yx, mut xy, mut yy) = (1.0, 0.0, 0.0, 1.0);
+            if flags & WE_HAVE_A_SCALE != 0 {
+                xx = read_f2dot14(data, offset)?;
+                yy = xx;
+                offset += 2;
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                xx = read_f2dot14(data, offset)?;
+                yy = read_f2dot14(data, offset + 2)?;
+                offset += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                xx = read_f2dot14(data, offset)?;
+                yx = read_f2dot14(data, offset + 2)?;
+                xy = read_f2dot14(data, offset + 4)?;
+                yy = read_f2dot14(data, offset + 6)?;
+                offset += 8;
+            }
+            // Component transform first, then the parent's
+            let [pxx, pyx, pxy, pyy, pdx, pdy] = parent;
+            let transform = [
+                xx * pxx + yx * pxy,
+                xx * pyx + yx * pyy,
+                xy * pxx + yy * pxy,
+                xy * pyx + yy * pyy,
+                dx * pxx + dy * pxy + pdx,
+                dx * pyx + dy * pyy + pdy,
+            ];
+            self.append_outline(component, transform, depth + 1, out);
+            if flags & MORE_COMPONENTS == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+/// Best Unicode subtable: full repertoire (format 12) over BMP (format 4)
+fn cmap_subtable(data: &[u8], cmap: Table) -> Option<usize> {
+    let count = read_u16(data, cmap.offset + 2)? as usize;
+    let mut best = None;
+    for index in 0..count {
+        let record = cmap.offset + 4 + index * 8;
+        let platform = read_u16(data, record)?;
+        let encoding = read_u16(data, record + 2)?;
+        let offset = cmap.offset + read_u32(data, record + 4)? as usize;
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        let rank = match read_u16(data, offset) {
+            Some(12) if unicode => 2,
+            Some(4) if unicode => 1,
+            _ => continue,
+        };
+        if best.is_none_or(|(best_rank, _)| rank > best_rank) {
+            best = Some((rank, offset));
+        }
+    }
+    best.map(|(_, offset)| offset)
+}
+
+/// First horizontal format 0 subtable of a version 0 kern table
+fn kern_subtable(data: &[u8], kern: Table) -> Option<(usize, usize)> {
+    if read_u16(data, kern.offset)? != 0 {
+        return None;
+    }
+    let count = read_u16(data, kern.offset + 2)?;
+    let mut subtable = kern.offset + 4;
+    for _ in 0..count {
+        let length = read_u16(data, subtable + 2)? as usize;
+        let coverage = read_u16(data, subtable + 4)?;
+        // Format in the high byte, bit 0 horizontal, bit 1 minimum values
+        if coverage >> 8 == 0 && coverage & 0x3 == 0x1 {
+            let pairs = read_u16(data, subtable + 6)? as usize;
+            if subtable + 14 + pairs * 6 > kern.offset + kern.length {
+                return None;
+            }
+            return Some((subtable + 14, pairs));
+        }
+        subtable += length;
+    }
+    None
+}
+
+fn append_simple(data: &[u8], contours: usize, transform: &[f32; 6], out: &mut Vec<Segment>) -> Option<()> {
+    let mut ends = Vec::with_capacity(contours);
+    for index in 0..contours {
+        ends.push(read_u16(data, 10 + index * 2)? as usize);
+    }
+    let point_count = ends.last().map_or(0, |&last| last + 1);
+    let instructions = read_u16(data, 10 + contours * 2)? as usize;
+    let mut offset = 12 + contours * 2 + instructions;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = *data.get(offset)?;
+        offset += 1;
+        let mut repeat = 1;
+        if flag & FLAG_REPEAT != 0 {
+            repeat += *data.get(offset)? as usize;
+            offset += 1;
+        }
+        for _ in 0..repeat.min(point_count - flags.len()) {
+            flags.push(flag);
+        }
+    }
+
+    // Coordinates are deltas: one byte with a sign flag, or two bytes,
+    // or nothing when repeating the previous value
+    let mut read_coordinates = |short: u8, same_or_positive: u8| -> Option<Vec<i32>> {
+        let mut values = Vec::with_capacity(point_count);
+        let mut value = 0i32;
+        for &flag in flags.iter() {
+            if flag & short != 0 {
+                let delta = *data.get(offset)? as i32;
+                offset += 1;
+                value += if flag & same_or_positive != 0 { delta } else { -delta };
+            } else if flag & same_or_positive == 0 {
+                value += read_i16(data, offset)? as i32;
+                offset += 2;
+            }
+            values.push(value);
+        }
+        Some(values)
+    };
+    let xs = read_coordinates(FLAG_X_SHORT, FLAG_X_SAME_OR_POSITIVE)?;
+    let ys = read_coordinates(FLAG_Y_SHORT, FLAG_Y_SAME_OR_POSITIVE)?;
+
+    let [xx, yx, xy, yy, dx, dy] = *transform;
+    let point = |index: usize| {
+        let (x, y) = (xs[index] as f32, ys[index] as f32);
+        Point::new(x * xx + y * xy + dx, x * yx + y * yy + dy)
+    };
+    let mut start = 0;
+    for &end in ends.iter() {
+        if end < start || end >= point_count {
+            return None;
+        }
+        append_contour(&flags[start..=end], |index| point(start + index), out);
+        start = end + 1;
+    }
+    Some(())
+}
+
+/// Turn one contour into segments; two consecutive off-curve points
+/// imply an on-curve point halfway between them
+fn append_contour(flags: &[u8], point: impl Fn(usize) -> Point, out: &mut Vec<Segment>) {
+    let count = flags.len();
+    let on_curve = |index: usize| flags[index % count] & FLAG_ON_CURVE != 0;
+    let midpoint = |a: Point, b: Point| Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+
+    // Start from an on-curve point, implied if every point is off-curve
+    let (first, start) = match (0..count).find(|&index| on_curve(index)) {
+        Some(index) => (point(index), index),
+        None => (midpoint(point(0), point(1 % count)), 0),
+    };
+    let mut current = first;
+    let mut control: Option<Point> = None;
+    for step in 1..=count {
+        let index = (start + step) % count;
+        let next = point(index);
+        match (on_curve(index), control) {
+            (true, None) => {
+                out.push(Segment::Line(current, next));
+                current = next;
+            }
+            (true, Some(off)) => {
+                out.push(Segment::Quad(current, off, next));
+                current = next;
+                control = None;
+            }
+            (false, None) => control = Some(next),
+            (false, Some(off)) => {
+                let implied = midpoint(off, next);
+                out.push(Segment::Quad(current, off, implied));
+                current = implied;
+                control = Some(next);
+            }
+        }
+    }
+    match control {
+        Some(off) => out.push(Segment::Quad(current, off, first)),
+        None if current != first => out.push(Segment::Line(current, first)),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn table(out: &mut Vec<u8>, records: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+        records.extend_from_slice(tag);
+        records.extend_from_slice(&0u32.to_be_bytes());
+        records.extend_from_slice(&(out.len() as u32).to_be_bytes());
+        records.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn be16(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|&value| (value as u16).to_be_bytes()).collect()
+    }
+
+    /// Font with 1000 units per em: glyph 1 ('A') a 600x700 box advancing
+    /// 700, glyph 2 ('V') a composite of glyph 1 shifted by 100, glyph 3
+    /// (' ') empty; A-V kerned by -80
+    pub(crate) fn test_font() -> Vec<u8> {
+        let mut head = vec![0u8; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        let mut maxp = vec![0u8; 6];
+        maxp[4..6].copy_from_slice(&4u16.to_be_bytes());
+        let mut hhea = vec![0u8; 36];
+        hhea[4..10].copy_from_slice(&be16(&[800, -200, 100]));
+        hhea[34..36].copy_from_slice(&4u16.to_be_bytes());
+        let hmtx = be16(&[500, 0, 700, 0, 700, 100, 250, 0]);
+
+        // cmap format 4: 'A' delta -64, 'V' delta -84, ' ' delta -29, end
+        let mut cmap = be16(&[0, 1, 3, 1, 0, 12]);
+        cmap.extend(be16(&[4, 48, 0, 8, 4, 1, 4]));
+        cmap.extend(be16(&[0x20, 0x41, 0x56, 0xffff, 0, 0x20, 0x41, 0x56, 0xffff]));
+        cmap.extend(be16(&[-29, -64, -84, 1, 0, 0, 0, 0]));
+
+        let mut a = be16(&[1, 0, 0, 600, 700, 3, 0]);
+        a.extend([FLAG_ON_CURVE; 4]);
+        a.extend(be16(&[0, 600, 0, -600]));
+        a.extend(be16(&[0, 0, 700, 0]));
+        let mut v = be16(&[-1, 100, 0, 700, 700]);
+        v.extend(be16(&[(ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES) as i32, 1, 100, 0]));
+        let mut glyf = a.clone();
+        glyf.extend(&v);
+        let loca = be16(&[0, 0, a.len() as i32 / 2, glyf.len() as i32 / 2, glyf.len() as i32 / 2]);
+
+        let mut kern = be16(&[0, 1, 0, 20, 0x0001, 1, 6, 0, 0]);
+        kern.extend(be16(&[1, 2, -80]));
+
+        let tables: [(&[u8; 4], Vec<u8>); 8] = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"kern", kern),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+        let mut header = vec![0, 1, 0, 0, 0, tables.len() as u8, 0, 0, 0, 0, 0, 0];
+        let mut body = vec![0u8; 12 + 16 * tables.len()];
+        let mut records = Vec::new();
+        for (tag, data) in tables.iter() {
+            table(&mut body, &mut records, tag, data);
+        }
+        header.extend(records);
+        body[..header.len()].copy_from_slice(&header);
+        body
+    }
+
+    #[test]
+    fn test_maps_and_measures_glyphs() {
+        let font = TrueTypeFont::parse(test_font()).unwrap();
+        assert_eq!(font.units_per_em(), 1000);
+        assert_eq!(font.vertical_metrics(), (800, -200, 100));
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('V'), Some(2));
+        assert_eq!(font.glyph_index(' '), Some(3));
+        assert_eq!(font.glyph_index('B'), None);
+        assert_eq!(font.horizontal_metrics(2), (700, 100));
+        assert_eq!(font.kerning(1, 2), -80);
+        assert_eq!(font.kerning(2, 1), 0);
+        assert_eq!(font.bounding_box(3), None);
+    }
+
+    #[test]
+    fn test_builds_outlines() {
+        let font = TrueTypeFont::parse(test_font()).unwrap();
+        let square = |dx: f32| {
+            let corner = |x: f32, y: f32| Point::new(x + dx, y);
+            vec![
+                Segment::Line(corner(0.0, 0.0), corner(600.0, 0.0)),
+                Segment::Line(corner(600.0, 0.0), corner(600.0, 700.0)),
+                Segment::Line(corner(600.0, 700.0), corner(0.0, 700.0)),
+                Segment::Line(corner(0.0, 700.0), corner(0.0, 0.0)),
+            ]
+        };
+        assert_eq!(font.outline(1), square(0.0));
+        assert_eq!(font.outline(2), square(100.0));
+
+        // Off-curve points only: four quadratic arcs through midpoints
+        let mut segments = Vec::new();
+        let corners = [Point::new(0.0, 0.0), Point::new(2.0, 0.0), Point::new(2.0, 2.0), Point::new(0.0, 2.0)];
+        append_contour(&[0; 4], |index| corners[index], &mut segments);
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0], Segment::Quad(Point::new(1.0, 0.0), corners[1], Point::new(2.0, 1.0)));
+        assert_eq!(segments[3], Segment::Quad(Point::new(0.0, 1.0), corners[0], Point::new(1.0, 0.0)));
+    }
+}
@@ -0,0 +1,24 @@
+/*
+ * Orion Operating System - Thermal Zone Reporting
+ *
+ * Client side of the thermal server for drivers owning a temperature
+ * sensor: the GPU, NVMe drives, the battery. A driver keeps one
+ * ThermalSensor per sensor and hands it every reading; the sensor
+ * registers its zone on first use, forwards readings often enough to
+ * keep the zone fresh and at once when the temperature moves, and
+ * registers again when the server restarted and forgot the zone.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod sensor;
+
+pub use sensor::{decikelvin_to_mc, kelvin_to_mc, ThermalSensor, Transport};
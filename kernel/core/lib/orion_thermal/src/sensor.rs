@@ -0,0 +1,204 @@
+/*
+ * Orion Operating System - Thermal Sensor Client
+ *
+ * Requests of the thermal server protocol used by providers (mirror of
+ * services/thermal/src/protocol.rs): REGISTER_ZONE and REPORT. The
+ * transport is a trait so drivers call through their IPC channel and
+ * tests through a fake server. A zone not reported for 30 seconds goes
+ * stale on the server, so readings are forwarded at least every
+ * REPORT_INTERVAL_NS even while the temperature holds still.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const OP_REGISTER_ZONE: u32 = 1;
+const OP_REPORT: u32 = 2;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+
+const NAME_SIZE: usize = 16;
+
+/// Longest pause between two reports of a steady temperature
+pub const REPORT_INTERVAL_NS: u64 = 5_000_000_000;
+/// Change reported without waiting for the interval, millidegrees
+pub const REPORT_DELTA_MC: i32 = 1000;
+
+/// Carries a request to the thermal server and returns its reply
+pub trait Transport {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Millidegrees Celsius of a temperature in kelvins (NVMe SMART)
+pub fn kelvin_to_mc(kelvin: u16) -> i32 {
+    (kelvin as i32 - 273) * 1000 - 150
+}
+
+/// Millidegrees Celsius of a temperature in tenths of kelvins (Smart Battery)
+pub fn decikelvin_to_mc(decikelvin: u16) -> i32 {
+    decikelvin as i32 * 100 - 273_150
+}
+
+fn status(response: &[u8]) -> Option<i32> {
+    let bytes = response.get(..4)?;
+    Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub struct ThermalSensor {
+    name: String,
+    passive_mc: i32,
+    critical_mc: i32,
+    zone: Option<u32>,
+    last_report_ns: Option<u64>,
+    last_temp_mc: i32,
+}
+
+impl ThermalSensor {
+    /// Sensor for zone `name` (at most 16 bytes), with the trips the
+    /// server adds on registration; 0 leaves a trip out
+    pub fn new(name: &str, passive_mc: i32, critical_mc: i32) -> Self {
+        let mut name = String::from(name);
+        while name.len() > NAME_SIZE {
+            name.pop();
+        }
+        Self { name, passive_mc, critical_mc, zone: None, last_report_ns: None, last_temp_mc: 0 }
+    }
+
+    /// Zone id given by the server, once registered
+    pub fn zone(&self) -> Option<u32> {
+        self.zone
+    }
+
+    /// Whether a reading taken now would be forwarded
+    pub fn due(&self, temp_mc: i32, now_ns: u64) -> bool {
+        match self.last_report_ns {
+            Some(last) => {
+                now_ns.saturating_sub(last) >= REPORT_INTERVAL_NS
+                    || (temp_mc - self.last_temp_mc).abs() >= REPORT_DELTA_MC
+            }
+            None => true,
+        }
+    }
+
+    fn register<T: Transport + ?Sized>(&mut self, transport: &mut T) -> Option<u32> {
+        let mut request = OP_REGISTER_ZONE.to_le_bytes().to_vec();
+        request.extend_from_slice(self.name.as_bytes());
+        request.resize(4 + NAME_SIZE, 0);
+        request.extend_from_slice(&self.passive_mc.to_le_bytes());
+        request.extend_from_slice(&self.critical_mc.to_le_bytes());
+        let response = transport.call(&request)?;
+        if status(&response)? != STATUS_OK {
+            return None;
+        }
+        let bytes = response.get(4..8)?;
+        let zone = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        self.zone = Some(zone);
+        Some(zone)
+    }
+
+    /// Forward a reading when it is due; returns whether the server took it
+    pub fn report<T: Transport + ?Sized>(&mut self, transport: &mut T, temp_mc: i32, now_ns: u64) -> bool {
+        if !self.due(temp_mc, now_ns) {
+            return false;
+        }
+        // A restarted server no longer knows the zone: register once more
+        for _ in 0..2 {
+            let zone = match self.zone {
+                Some(zone) => zone,
+                None => match self.register(transport) {
+                    Some(zone) => zone,
+                    None => return false,
+                },
+            };
+            let mut request = OP_REPORT.to_le_bytes().to_vec();
+            request.extend_from_slice(&zone.to_le_bytes());
+            request.extend_from_slice(&temp_mc.to_le_bytes());
+            match transport.call(&request).as_deref().and_then(status) {
+                Some(STATUS_OK) => {
+                    self.last_report_ns = Some(now_ns);
+                    self.last_temp_mc = temp_mc;
+                    return true;
+                }
+                Some(STATUS_ENOENT) | Some(STATUS_EPERM) => self.zone = None,
+                _ => return false,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Server keeping the zones it registered; a restarted one hands out ids from `offset`
+    #[derive(Default)]
+    struct FakeServer {
+        zones: Vec<String>,
+        reports: Vec<(u32, i32)>,
+        offset: u32,
+    }
+
+    impl Transport for FakeServer {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            let field = |offset: usize| u32::from_le_bytes(request[offset..offset + 4].try_into().unwrap());
+            let mut reply = STATUS_OK.to_le_bytes().to_vec();
+            match field(0) {
+                OP_REGISTER_ZONE => {
+                    let name = core::str::from_utf8(&request[4..20]).unwrap().trim_end_matches('\0');
+                    self.zones.push(String::from(name));
+                    reply.extend_from_slice(&(self.offset + self.zones.len() as u32 - 1).to_le_bytes());
+                }
+                OP_REPORT => {
+                    let zone = field(4);
+                    if zone < self.offset || zone - self.offset >= self.zones.len() as u32 {
+                        return Some(STATUS_ENOENT.to_le_bytes().to_vec());
+                    }
+                    self.reports.push((zone, field(8) as i32));
+                }
+                _ => return None,
+            }
+            Some(reply)
+        }
+    }
+
+    #[test]
+    fn test_registers_and_rate_limits() {
+        let mut server = FakeServer::default();
+        let mut sensor = ThermalSensor::new("nvme0", 70_000, 80_000);
+        assert!(sensor.report(&mut server, 40_000, 0));
+        assert_eq!(sensor.zone(), Some(0));
+        assert_eq!(server.zones, vec![String::from("nvme0")]);
+
+        // Steady readings wait for the interval, jumps do not
+        assert!(!sensor.report(&mut server, 40_500, 1_000_000_000));
+        assert!(sensor.report(&mut server, 41_000, 2_000_000_000));
+        assert!(sensor.report(&mut server, 41_000, 2_000_000_000 + REPORT_INTERVAL_NS));
+        assert_eq!(server.reports, vec![(0, 40_000), (0, 41_000), (0, 41_000)]);
+    }
+
+    #[test]
+    fn test_registers_again_after_server_restart() {
+        let mut server = FakeServer::default();
+        let mut sensor = ThermalSensor::new("a-very-long-gpu-sensor-name", 0, 0);
+        assert!(sensor.report(&mut server, 50_000, 0));
+        assert_eq!(server.zones[0], "a-very-long-gpu-");
+
+        server = FakeServer { offset: 3, ..FakeServer::default() };
+        assert!(sensor.report(&mut server, 60_000, 1));
+        assert_eq!(sensor.zone(), Some(3));
+        assert_eq!(server.reports, vec![(3, 60_000)]);
+
+        assert_eq!(kelvin_to_mc(318), 44_850);
+        assert_eq!(decikelvin_to_mc(2982), 25_050);
+    }
+}
@@ -0,0 +1,526 @@
+/*
+ * Orion Operating System - Optimization Engine
+ *
+ * A feedback controller per volume. The driver serving the volume hands
+ * it the figures of an interval (a Sample: reads, cache hits, read-ahead
+ * use, read latency, what compression made of the blocks it was tried
+ * on) and applies the Settings it answers with:
+ *
+ *   cache size   grows by the step of the bounds while the hit rate is
+ *                under target; a growth that did not raise the hit rate
+ *                is taken back and growth stops until the hit rate falls
+ *                clearly below that plateau
+ *   read-ahead   doubles while reads are sequential and what was read
+ *                ahead gets used, halves when reads turn random or most
+ *                of it is wasted
+ *   compression  of cached blocks, turned on when blocks compress well,
+ *                off when they stop to or when reads get slower than the
+ *                latency bound (only under CompressionPolicy::Auto)
+ *
+ * Nothing moves outside the bounds the administrator set for the volume,
+ * and a volume without bounds is not tuned at all. A knob moves only
+ * once the same change was called for STREAK intervals in a row, and then
+ * stays put for COOLDOWN intervals, so that one burst does not resize a
+ * cache and the effect of a change is measured before the next one.
+ * Intervals with fewer than MIN_READS reads leave read figures alone.
+ * Every change goes to the decision log (see log.rs).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use crate::log::{DecisionLog, Knob, Reason};
+
+/// Reads an interval needs before its read figures count
+pub const MIN_READS: u64 = 32;
+/// Intervals in a row calling for the same change before it is made
+pub const STREAK: u32 = 3;
+/// Intervals a knob stays put after it moved
+pub const COOLDOWN: u32 = 3;
+
+/// Hit rate the cache grows towards, ppm
+const HIT_RATE_TARGET: u32 = 900_000;
+/// Least gain a cache growth has to buy, ppm
+const HIT_RATE_MIN_GAIN: u32 = 20_000;
+/// Read-ahead grows above this share of sequential reads, shrinks below
+/// the low one, ppm
+const SEQUENTIAL_HIGH: u32 = 600_000;
+const SEQUENTIAL_LOW: u32 = 200_000;
+/// Read-ahead grows only while less than this share of it is wasted, and
+/// shrinks above the high one, ppm
+const WASTE_LOW: u32 = 250_000;
+const WASTE_HIGH: u32 = 500_000;
+/// Compression ratios turning compression on and off, hundredths
+const RATIO_ON: u32 = 150;
+const RATIO_OFF: u32 = 120;
+
+const PPM: u64 = 1_000_000;
+
+/// Figures of one interval, counted by the volume's cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// Read requests
+    pub reads: u64,
+    /// Read requests starting where the previous one ended
+    pub sequential_reads: u64,
+    pub blocks_read: u64,
+    /// Blocks read found in the cache
+    pub cache_hits: u64,
+    /// Blocks read ahead, and those of them a read then found
+    pub readahead_blocks: u64,
+    pub readahead_used: u64,
+    /// Time spent serving the reads, ns
+    pub read_ns: u64,
+    /// Bytes the compressor was tried on and what it made of them
+    pub compressed_in: u64,
+    pub compressed_out: u64,
+}
+
+fn ratio_ppm(part: u64, whole: u64) -> u32 {
+    (part.min(whole) * PPM / whole.max(1)) as u32
+}
+
+impl Sample {
+    pub fn hit_rate_ppm(&self) -> u32 {
+        ratio_ppm(self.cache_hits, self.blocks_read)
+    }
+
+    pub fn sequential_ppm(&self) -> u32 {
+        ratio_ppm(self.sequential_reads, self.reads)
+    }
+
+    /// Share of the blocks read ahead that no read found
+    pub fn readahead_waste_ppm(&self) -> u32 {
+        ratio_ppm(self.readahead_blocks - self.readahead_used.min(self.readahead_blocks), self.readahead_blocks)
+    }
+
+    /// Bytes in per byte out, in hundredths; None when nothing was tried
+    pub fn compression_ratio(&self) -> Option<u32> {
+        if self.compressed_out == 0 {
+            return None;
+        }
+        Some((self.compressed_in * 100 / self.compressed_out).min(u32::MAX as u64) as u32)
+    }
+
+    pub fn mean_read_ns(&self) -> u64 {
+        self.read_ns / self.reads.max(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionPolicy {
+    /// The controller decides
+    Auto,
+    On,
+    Off,
+}
+
+impl CompressionPolicy {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(CompressionPolicy::Auto),
+            1 => Some(CompressionPolicy::On),
+            2 => Some(CompressionPolicy::Off),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            CompressionPolicy::Auto => 0,
+            CompressionPolicy::On => 1,
+            CompressionPolicy::Off => 2,
+        }
+    }
+}
+
+/// What the administrator allows the controller for a volume. Equal
+/// bounds pin a knob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub cache_min: u64,
+    pub cache_max: u64,
+    /// Bytes the cache grows or shrinks by at a time
+    pub cache_step: u64,
+    pub readahead_min: u32,
+    pub readahead_max: u32,
+    pub compression: CompressionPolicy,
+    /// Mean read latency compression may not push reads over, ns, 0 for
+    /// no bound
+    pub latency_max_ns: u64,
+}
+
+impl Bounds {
+    pub fn is_valid(&self) -> bool {
+        self.cache_min <= self.cache_max
+            && (self.cache_step > 0 || self.cache_min == self.cache_max)
+            && self.readahead_min <= self.readahead_max
+    }
+
+    fn clamp(&self, settings: Settings) -> Settings {
+        Settings {
+            cache_bytes: settings.cache_bytes.clamp(self.cache_min, self.cache_max),
+            readahead_blocks: settings.readahead_blocks.clamp(self.readahead_min, self.readahead_max),
+            compression: match self.compression {
+                CompressionPolicy::Auto => settings.compression,
+                CompressionPolicy::On => true,
+                CompressionPolicy::Off => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Settings {
+    pub cache_bytes: u64,
+    pub readahead_blocks: u32,
+    pub compression: bool,
+}
+
+impl Settings {
+    fn get(&self, knob: Knob) -> u64 {
+        match knob {
+            Knob::CacheBytes => self.cache_bytes,
+            Knob::ReadaheadBlocks => self.readahead_blocks as u64,
+            Knob::Compression => self.compression as u64,
+        }
+    }
+
+    fn set(&mut self, knob: Knob, value: u64) {
+        match knob {
+            Knob::CacheBytes => self.cache_bytes = value,
+            Knob::ReadaheadBlocks => self.readahead_blocks = value as u32,
+            Knob::Compression => self.compression = value != 0,
+        }
+    }
+}
+
+/// Hysteresis of one knob
+#[derive(Debug, Clone, Copy, Default)]
+struct KnobState {
+    /// Value the last intervals called for, and for how many in a row
+    wanted: Option<u64>,
+    streak: u32,
+    cooldown: u32,
+}
+
+struct Tuned {
+    bounds: Bounds,
+    settings: Settings,
+    knobs: [KnobState; 3],
+    /// Hit rate before the last cache growth, until its effect is known
+    grown_from: Option<u32>,
+    /// Hit rate growth stopped at
+    plateau: Option<u32>,
+}
+
+fn knob_index(knob: Knob) -> usize {
+    match knob {
+        Knob::CacheBytes => 0,
+        Knob::ReadaheadBlocks => 1,
+        Knob::Compression => 2,
+    }
+}
+
+impl Tuned {
+    fn cache_change(&mut self, sample: &Sample) -> Option<(u64, Reason)> {
+        let (cache, bounds) = (self.settings.cache_bytes, &self.bounds);
+        let hit_rate = sample.hit_rate_ppm();
+        if let Some(before) = self.grown_from {
+            if hit_rate < before.saturating_add(HIT_RATE_MIN_GAIN) {
+                let shrunk = cache.saturating_sub(bounds.cache_step).max(bounds.cache_min);
+                return Some((shrunk, Reason::HitRatePlateau(hit_rate)));
+            }
+            self.grown_from = None;
+        }
+        if let Some(plateau) = self.plateau {
+            if hit_rate.saturating_add(HIT_RATE_MIN_GAIN) > plateau {
+                return None;
+            }
+            self.plateau = None;
+        }
+        if hit_rate < HIT_RATE_TARGET && cache < bounds.cache_max {
+            let grown = cache.saturating_add(bounds.cache_step).min(bounds.cache_max);
+            return Some((grown, Reason::LowHitRate(hit_rate)));
+        }
+        None
+    }
+
+    fn readahead_change(&self, sample: &Sample) -> Option<(u64, Reason)> {
+        let (readahead, bounds) = (self.settings.readahead_blocks, &self.bounds);
+        let sequential = sample.sequential_ppm();
+        let waste = sample.readahead_waste_ppm();
+        if sequential >= SEQUENTIAL_HIGH && waste <= WASTE_LOW && readahead < bounds.readahead_max {
+            let grown = readahead.saturating_mul(2).max(1).min(bounds.readahead_max);
+            return Some((grown as u64, Reason::Sequential(sequential)));
+        }
+        if readahead > bounds.readahead_min {
+            let shrunk = (readahead / 2).max(bounds.readahead_min) as u64;
+            if sample.readahead_blocks > 0 && waste >= WASTE_HIGH {
+                return Some((shrunk, Reason::ReadaheadWasted(waste)));
+            }
+            if sequential < SEQUENTIAL_LOW {
+                return Some((shrunk, Reason::Sequential(sequential)));
+            }
+        }
+        None
+    }
+
+    fn compression_change(&self, sample: &Sample, enough_reads: bool) -> Option<(u64, Reason)> {
+        if self.bounds.compression != CompressionPolicy::Auto {
+            return None;
+        }
+        let latency = sample.mean_read_ns();
+        let too_slow = enough_reads && self.bounds.latency_max_ns > 0 && latency > self.bounds.latency_max_ns;
+        let ratio = sample.compression_ratio();
+        if self.settings.compression {
+            if too_slow {
+                return Some((0, Reason::Latency(latency)));
+            }
+            match ratio {
+                Some(ratio) if ratio < RATIO_OFF => Some((0, Reason::Incompressible(ratio))),
+                _ => None,
+            }
+        } else {
+            match ratio {
+                Some(ratio) if ratio >= RATIO_ON && !too_slow => Some((1, Reason::Compressible(ratio))),
+                _ => None,
+            }
+        }
+    }
+
+    /// Count the change an interval calls for; Some once it is due
+    fn want(&mut self, knob: Knob, change: Option<(u64, Reason)>) -> Option<(u64, Reason)> {
+        let state = &mut self.knobs[knob_index(knob)];
+        if state.cooldown > 0 {
+            state.cooldown -= 1;
+            return None;
+        }
+        let Some((value, reason)) = change else {
+            *state = KnobState::default();
+            return None;
+        };
+        if state.wanted == Some(value) {
+            state.streak += 1;
+        } else {
+            *state = KnobState { wanted: Some(value), streak: 1, cooldown: 0 };
+        }
+        if state.streak < STREAK {
+            return None;
+        }
+        *state = KnobState { wanted: None, streak: 0, cooldown: COOLDOWN };
+        Some((value, reason))
+    }
+}
+
+pub struct OptimizationEngine {
+    volumes: BTreeMap<String, Tuned>,
+    log: DecisionLog,
+}
+
+impl Default for OptimizationEngine {
+    fn default() -> Self {
+        Self::new(DecisionLog::default())
+    }
+}
+
+impl OptimizationEngine {
+    pub fn new(log: DecisionLog) -> Self {
+        Self { volumes: BTreeMap::new(), log }
+    }
+
+    /// Tune `volume` within `bounds`, or within new ones; the settings
+    /// are brought inside them at once. A volume tuned for the first time
+    /// starts at its minimums. None when the bounds are inconsistent.
+    pub fn set_bounds(&mut self, volume: &str, bounds: Bounds, now_ns: u64) -> Option<Settings> {
+        if !bounds.is_valid() {
+            return None;
+        }
+        let tuned = self.volumes.entry(String::from(volume)).or_insert(Tuned {
+            bounds,
+            settings: Settings::default(),
+            knobs: Default::default(),
+            grown_from: None,
+            plateau: None,
+        });
+        let old = tuned.settings;
+        let settings = bounds.clamp(old);
+        *tuned = Tuned { bounds, settings, knobs: Default::default(), grown_from: None, plateau: None };
+        for knob in [Knob::CacheBytes, Knob::ReadaheadBlocks, Knob::Compression] {
+            if old.get(knob) != settings.get(knob) {
+                self.log.record(now_ns, volume, knob, old.get(knob), settings.get(knob), Reason::Bounds);
+            }
+        }
+        Some(settings)
+    }
+
+    /// Stop tuning `volume`
+    pub fn remove(&mut self, volume: &str) -> bool {
+        self.volumes.remove(volume).is_some()
+    }
+
+    pub fn bounds(&self, volume: &str) -> Option<Bounds> {
+        self.volumes.get(volume).map(|tuned| tuned.bounds)
+    }
+
+    pub fn settings(&self, volume: &str) -> Option<Settings> {
+        self.volumes.get(volume).map(|tuned| tuned.settings)
+    }
+
+    /// Feed the figures of one interval of `volume`; answers the new
+    /// settings when they changed
+    pub fn observe(&mut self, volume: &str, sample: &Sample, now_ns: u64) -> Option<Settings> {
+        let tuned = self.volumes.get_mut(volume)?;
+        let enough_reads = sample.reads >= MIN_READS;
+        let cache = if enough_reads { tuned.cache_change(sample) } else { None };
+        let readahead = if enough_reads { tuned.readahead_change(sample) } else { None };
+        let compression = tuned.compression_change(sample, enough_reads);
+
+        let old = tuned.settings;
+        for (knob, change) in
+            [(Knob::CacheBytes, cache), (Knob::ReadaheadBlocks, readahead), (Knob::Compression, compression)]
+        {
+            if !enough_reads && knob != Knob::Compression {
+                continue;
+            }
+            let Some((value, reason)) = tuned.want(knob, change) else {
+                continue;
+            };
+            match reason {
+                Reason::LowHitRate(hit_rate) => tuned.grown_from = Some(hit_rate),
+                Reason::HitRatePlateau(hit_rate) => (tuned.grown_from, tuned.plateau) = (None, Some(hit_rate)),
+                _ => {}
+            }
+            self.log.record(now_ns, volume, knob, tuned.settings.get(knob), value, reason);
+            tuned.settings.set(knob, value);
+        }
+        (tuned.settings != old).then_some(tuned.settings)
+    }
+
+    pub fn log(&self) -> &DecisionLog {
+        &self.log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const MIB: u64 = 1 << 20;
+
+    fn bounds() -> Bounds {
+        Bounds {
+            cache_min: MIB,
+            cache_max: 4 * MIB,
+            cache_step: MIB,
+            readahead_min: 0,
+            readahead_max: 32,
+            compression: CompressionPolicy::Auto,
+            latency_max_ns: 200_000,
+        }
+    }
+
+    /// 100 reads of ten blocks each
+    fn reads(hits: u64, sequential: u64, latency_ns: u64) -> Sample {
+        Sample {
+            reads: 100,
+            sequential_reads: sequential,
+            blocks_read: 1000,
+            cache_hits: hits,
+            read_ns: 100 * latency_ns,
+            ..Sample::default()
+        }
+    }
+
+    fn run(engine: &mut OptimizationEngine, sample: Sample, intervals: u32) -> Settings {
+        for _ in 0..intervals {
+            engine.observe("data", &sample, 0);
+        }
+        engine.settings("data").unwrap()
+    }
+
+    #[test]
+    fn test_grows_the_cache_until_it_stops_paying() {
+        let mut engine = OptimizationEngine::default();
+        assert_eq!(engine.observe("data", &reads(10, 0, 1000), 0), None);
+        let mut pinned = bounds();
+        pinned.cache_step = 0;
+        assert_eq!(engine.set_bounds("data", pinned, 0), None);
+        assert_eq!(engine.set_bounds("data", bounds(), 0).unwrap().cache_bytes, MIB);
+
+        // One low interval is not enough, and quiet intervals do not count
+        assert_eq!(engine.observe("data", &reads(500, 0, 1000), 0), None);
+        assert_eq!(engine.observe("data", &Sample { reads: 3, ..Sample::default() }, 0), None);
+        assert_eq!(run(&mut engine, reads(500, 0, 1000), 2).cache_bytes, 2 * MIB);
+        // Cooling down, then the growth paid: grow again
+        assert_eq!(run(&mut engine, reads(700, 0, 1000), 3).cache_bytes, 2 * MIB);
+        assert_eq!(run(&mut engine, reads(700, 0, 1000), 3).cache_bytes, 3 * MIB);
+        // This one did not: take it back and stay there
+        assert_eq!(run(&mut engine, reads(705, 0, 1000), 6).cache_bytes, 2 * MIB);
+        assert_eq!(run(&mut engine, reads(700, 0, 1000), 12).cache_bytes, 2 * MIB);
+        // Until the workload changes
+        assert_eq!(run(&mut engine, reads(300, 0, 1000), 6).cache_bytes, 3 * MIB);
+
+        assert_eq!((engine.log().first_seq(), engine.log().last_seq()), (1, 5));
+        let reasons: Vec<_> = engine.log().since(0).map(|decision| (decision.old, decision.reason)).collect();
+        assert_eq!(
+            reasons,
+            [
+                (0, Reason::Bounds),
+                (MIB, Reason::LowHitRate(500_000)),
+                (2 * MIB, Reason::LowHitRate(700_000)),
+                (3 * MIB, Reason::HitRatePlateau(705_000)),
+                (2 * MIB, Reason::LowHitRate(300_000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_follows_sequential_reads_with_read_ahead() {
+        let mut engine = OptimizationEngine::default();
+        engine.set_bounds("data", bounds(), 0);
+        let mut sequential = reads(950, 90, 1000);
+        assert_eq!(run(&mut engine, sequential, 3).readahead_blocks, 1);
+        sequential.readahead_blocks = 100;
+        sequential.readahead_used = 90;
+        assert_eq!(run(&mut engine, sequential, 6).readahead_blocks, 2);
+        assert_eq!(run(&mut engine, sequential, 6).readahead_blocks, 4);
+        sequential.readahead_used = 30;
+        assert_eq!(run(&mut engine, sequential, 6).readahead_blocks, 2);
+        assert_eq!(run(&mut engine, reads(950, 5, 1000), 6).readahead_blocks, 1);
+
+        let mut narrow = bounds();
+        narrow.readahead_max = 0;
+        assert_eq!(engine.set_bounds("data", narrow, 0).unwrap().readahead_blocks, 0);
+        assert_eq!(engine.log().since(0).last().unwrap().reason, Reason::Bounds);
+    }
+
+    #[test]
+    fn test_compresses_while_it_pays_and_reads_stay_fast() {
+        let mut engine = OptimizationEngine::default();
+        engine.set_bounds("data", bounds(), 0);
+        let mut sample = reads(950, 0, 50_000);
+        sample.compressed_in = 40_960;
+        sample.compressed_out = 16_384;
+        assert!(run(&mut engine, sample, 3).compression);
+        // Slower than the bound: off even though it compresses
+        let mut slow = sample;
+        slow.read_ns = 100 * 300_000;
+        assert!(!run(&mut engine, slow, 6).compression);
+        assert_eq!(engine.log().since(0).last().unwrap().reason, Reason::Latency(300_000));
+
+        let mut pinned = bounds();
+        pinned.compression = CompressionPolicy::Off;
+        engine.set_bounds("data", pinned, 0);
+        assert!(!run(&mut engine, sample, 12).compression);
+        pinned.compression = CompressionPolicy::On;
+        assert!(engine.set_bounds("data", pinned, 0).unwrap().compression);
+    }
+}
@@ -0,0 +1,32 @@
+/*
+ * Orion Operating System - Storage Tuning
+ *
+ * The optimization engine of the storage stack: a feedback controller
+ * that watches the hit rate, read-ahead use, read latency and
+ * compression ratio of each volume and adjusts its read cache size,
+ * read-ahead window and cache compression within the bounds the
+ * administrator set, logging every decision for review.
+ *
+ * The volume cache the knobs act on lives here as well, and counts the
+ * figures the engine is fed, so a driver only has to route volume reads
+ * and writes through it and apply what the engine answers. The LVM
+ * driver does so for the volumes it exports.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod cache;
+pub mod engine;
+pub mod log;
+
+pub use cache::VolumeCache;
+pub use engine::{Bounds, CompressionPolicy, OptimizationEngine, Sample, Settings};
+pub use log::{Decision, DecisionLog, Knob, Reason};
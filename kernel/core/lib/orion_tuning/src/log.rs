@@ -0,0 +1,177 @@
+/*
+ * Orion Operating System - Tuning Decisions
+ *
+ * Every change the controller makes to a volume is recorded with what
+ * it measured and why, so that an administrator can review what the
+ * engine did and tighten its bounds if they disagree. The log keeps the
+ * latest decisions only; each has a sequence number, increasing for the
+ * life of the engine, so that a reader polling it can ask for what came
+ * after the last one it saw and tell when it fell behind.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+/// Setting of a volume the controller adjusts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Knob {
+    /// Read cache size in bytes
+    CacheBytes,
+    /// Blocks read ahead of a sequential read
+    ReadaheadBlocks,
+    /// Cached blocks kept compressed, 0 or 1
+    Compression,
+}
+
+impl Knob {
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Knob::CacheBytes => 1,
+            Knob::ReadaheadBlocks => 2,
+            Knob::Compression => 3,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Knob::CacheBytes => "cache_bytes",
+            Knob::ReadaheadBlocks => "readahead_blocks",
+            Knob::Compression => "compression",
+        }
+    }
+}
+
+/// Why a knob moved, with the figure that decided it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The administrator changed the bounds; the figure is unused
+    Bounds,
+    /// Hit rate below target, in parts per million
+    LowHitRate(u32),
+    /// The last growth gained less than it should have, hit rate ppm
+    HitRatePlateau(u32),
+    /// Share of sequential reads, ppm
+    Sequential(u32),
+    /// Share of read-ahead blocks never read, ppm
+    ReadaheadWasted(u32),
+    /// Compression ratio, in hundredths
+    Compressible(u32),
+    /// Compression ratio, in hundredths
+    Incompressible(u32),
+    /// Mean read latency over the bound, ns
+    Latency(u64),
+}
+
+impl Reason {
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Reason::Bounds => 1,
+            Reason::LowHitRate(_) => 2,
+            Reason::HitRatePlateau(_) => 3,
+            Reason::Sequential(_) => 4,
+            Reason::ReadaheadWasted(_) => 5,
+            Reason::Compressible(_) => 6,
+            Reason::Incompressible(_) => 7,
+            Reason::Latency(_) => 8,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Reason::Bounds => "bounds",
+            Reason::LowHitRate(_) => "low-hit-rate",
+            Reason::HitRatePlateau(_) => "hit-rate-plateau",
+            Reason::Sequential(_) => "sequential",
+            Reason::ReadaheadWasted(_) => "readahead-wasted",
+            Reason::Compressible(_) => "compressible",
+            Reason::Incompressible(_) => "incompressible",
+            Reason::Latency(_) => "latency",
+        }
+    }
+
+    /// The figure that decided it
+    pub fn measured(self) -> u64 {
+        match self {
+            Reason::Bounds => 0,
+            Reason::LowHitRate(value)
+            | Reason::HitRatePlateau(value)
+            | Reason::Sequential(value)
+            | Reason::ReadaheadWasted(value)
+            | Reason::Compressible(value)
+            | Reason::Incompressible(value) => value as u64,
+            Reason::Latency(ns) => ns,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub seq: u64,
+    /// Monotonic time of the decision, ns
+    pub time_ns: u64,
+    pub volume: String,
+    pub knob: Knob,
+    pub old: u64,
+    pub new: u64,
+    pub reason: Reason,
+}
+
+/// Decisions the log keeps by default
+pub const DEFAULT_LOG_CAPACITY: usize = 256;
+
+pub struct DecisionLog {
+    entries: VecDeque<Decision>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl DecisionLog {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: VecDeque::new(), capacity: capacity.max(1), next_seq: 1 }
+    }
+
+    pub fn record(&mut self, time_ns: u64, volume: &str, knob: Knob, old: u64, new: u64, reason: Reason) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.push_back(Decision { seq, time_ns, volume: String::from(volume), knob, old, new, reason });
+    }
+
+    /// Decisions with a sequence number above `seq`, oldest first
+    pub fn since(&self, seq: u64) -> impl Iterator<Item = &Decision> {
+        self.entries.iter().filter(move |decision| decision.seq > seq)
+    }
+
+    /// Sequence number of the oldest decision still kept, or of the next
+    /// one while the log is empty; a reader behind it lost decisions
+    pub fn first_seq(&self) -> u64 {
+        self.entries.front().map_or(self.next_seq, |decision| decision.seq)
+    }
+
+    /// Sequence number of the latest decision, 0 before the first
+    pub fn last_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for DecisionLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_CAPACITY)
+    }
+}
@@ -0,0 +1,214 @@
+/*
+ * Orion Operating System - Signed Boot Configuration
+ *
+ * First block of a system image: what is needed to check the rest of it,
+ * signed with the same trailer as driver and firmware images
+ * (services/io/src/signing.rs), so release tooling signs it with the same
+ * keys and the driver checks it against the trust anchors of the I/O
+ * server. Whoever can change the image cannot change the root hash
+ * without the release key. All fields are little-endian:
+ *
+ *   0    4   magic "OVCF"
+ *   4    4   format version
+ *   8    4   block size, 4096
+ *   12   4   hash algorithm, 1 for SHA-256
+ *   16   8   data blocks
+ *   24   8   first block of the hash tree
+ *   32   32  salt
+ *   64   32  root hash
+ *   96   16  system version, ASCII padded with NULs
+ *   112  16  reserved
+ *   128      trailer: Ed25519 signature over the 128 bytes before (64),
+ *            key id (8), trailer version (4), magic "ORIONSIG" (8)
+ *
+ * The rest of the block is zeros.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{self, PUBLIC_KEY_SIZE, SECRET_KEY_SIZE, SIGNATURE_SIZE};
+use orion_crypto::sha512::Sha512;
+
+use crate::tree::{Digest, Geometry, DIGEST_SIZE, SALT_SIZE};
+use crate::{VerityError, BLOCK_SIZE};
+
+pub const CONFIG_MAGIC: &[u8; 4] = b"OVCF";
+pub const CONFIG_FORMAT: u32 = 1;
+pub const CONFIG_SIZE: usize = 128;
+pub const ALGORITHM_SHA256: u32 = 1;
+pub const VERSION_SIZE: usize = 16;
+
+/// Image block holding the configuration
+pub const CONFIG_BLOCK: u64 = 0;
+
+pub const SIGNATURE_MAGIC: [u8; 8] = *b"ORIONSIG";
+pub const TRAILER_VERSION: u32 = 1;
+pub const TRAILER_SIZE: usize = SIGNATURE_SIZE + 8 + 4 + 8;
+
+pub type KeyId = [u8; 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootConfig {
+    pub data_blocks: u64,
+    pub salt: [u8; SALT_SIZE],
+    pub root_hash: Digest,
+    pub version: [u8; VERSION_SIZE],
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+impl BootConfig {
+    pub fn data_start(&self) -> u64 {
+        CONFIG_BLOCK + 1
+    }
+
+    pub fn hash_start(&self) -> u64 {
+        self.data_start() + self.data_blocks
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        Geometry::new(self.data_blocks)
+    }
+
+    /// Blocks of the whole image
+    pub fn image_blocks(&self) -> u64 {
+        self.hash_start() + self.geometry().hash_blocks()
+    }
+
+    pub fn encode(&self) -> [u8; CONFIG_SIZE] {
+        let mut out = [0u8; CONFIG_SIZE];
+        out[..4].copy_from_slice(CONFIG_MAGIC);
+        out[4..8].copy_from_slice(&CONFIG_FORMAT.to_le_bytes());
+        out[8..12].copy_from_slice(&(BLOCK_SIZE as u32).to_le_bytes());
+        out[12..16].copy_from_slice(&ALGORITHM_SHA256.to_le_bytes());
+        out[16..24].copy_from_slice(&self.data_blocks.to_le_bytes());
+        out[24..32].copy_from_slice(&self.hash_start().to_le_bytes());
+        out[32..64].copy_from_slice(&self.salt);
+        out[64..96].copy_from_slice(&self.root_hash);
+        out[96..112].copy_from_slice(&self.version);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, VerityError> {
+        if data.len() < CONFIG_SIZE || &data[..4] != CONFIG_MAGIC || read_u32(data, 4) != CONFIG_FORMAT {
+            return Err(VerityError::Malformed);
+        }
+        if read_u32(data, 8) != BLOCK_SIZE as u32 || read_u32(data, 12) != ALGORITHM_SHA256 {
+            return Err(VerityError::Malformed);
+        }
+        let mut config = BootConfig {
+            data_blocks: read_u64(data, 16),
+            salt: [0; SALT_SIZE],
+            root_hash: [0; DIGEST_SIZE],
+            version: [0; VERSION_SIZE],
+        };
+        config.salt.copy_from_slice(&data[32..64]);
+        config.root_hash.copy_from_slice(&data[64..96]);
+        config.version.copy_from_slice(&data[96..112]);
+        // The tree follows the data; nothing else is supported
+        if read_u64(data, 24) != config.hash_start() {
+            return Err(VerityError::Malformed);
+        }
+        Ok(config)
+    }
+
+    /// System version as text, without the padding
+    pub fn version_str(&self) -> &str {
+        let end = self.version.iter().position(|&byte| byte == 0).unwrap_or(VERSION_SIZE);
+        core::str::from_utf8(&self.version[..end]).unwrap_or("")
+    }
+}
+
+/// Identifier of a public key as recorded in signature trailers
+pub fn key_id(public_key: &[u8; PUBLIC_KEY_SIZE]) -> KeyId {
+    let digest = Sha512::digest(public_key);
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&digest[..8]);
+    id
+}
+
+/// Check the configuration block of an image against `keys`
+pub fn verify(block: &[u8], keys: &[[u8; PUBLIC_KEY_SIZE]]) -> Result<BootConfig, VerityError> {
+    if block.len() < CONFIG_SIZE + TRAILER_SIZE {
+        return Err(VerityError::Malformed);
+    }
+    let (signed, trailer) = block[..CONFIG_SIZE + TRAILER_SIZE].split_at(CONFIG_SIZE);
+    if trailer[TRAILER_SIZE - 8..] != SIGNATURE_MAGIC {
+        return Err(VerityError::Rejected);
+    }
+    if read_u32(trailer, SIGNATURE_SIZE + 8) != TRAILER_VERSION {
+        return Err(VerityError::Malformed);
+    }
+    let config = BootConfig::decode(signed)?;
+
+    let mut signature = [0u8; SIGNATURE_SIZE];
+    signature.copy_from_slice(&trailer[..SIGNATURE_SIZE]);
+    let id = &trailer[SIGNATURE_SIZE..SIGNATURE_SIZE + 8];
+    let key = keys.iter().find(|key| key_id(key) == id).ok_or(VerityError::Rejected)?;
+    if !ed25519::verify(key, signed, &signature) {
+        return Err(VerityError::Rejected);
+    }
+    Ok(config)
+}
+
+/// Signed configuration block, as the release tooling builds it
+pub fn sign(config: &BootConfig, secret_key: &[u8; SECRET_KEY_SIZE]) -> Vec<u8> {
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+    block.extend_from_slice(&config.encode());
+    let signature = ed25519::sign(secret_key, &block);
+    block.extend_from_slice(&signature);
+    block.extend_from_slice(&key_id(&ed25519::public_key(secret_key)));
+    block.extend_from_slice(&TRAILER_VERSION.to_le_bytes());
+    block.extend_from_slice(&SIGNATURE_MAGIC);
+    block.resize(BLOCK_SIZE, 0);
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: [u8; SECRET_KEY_SIZE] = [11; SECRET_KEY_SIZE];
+
+    fn config() -> BootConfig {
+        BootConfig {
+            data_blocks: 1000,
+            salt: [1; SALT_SIZE],
+            root_hash: [2; DIGEST_SIZE],
+            version: *b"2025.08\0\0\0\0\0\0\0\0\0",
+        }
+    }
+
+    #[test]
+    fn test_signed_configuration_verifies() {
+        let keys = [ed25519::public_key(&SECRET)];
+        let block = sign(&config(), &SECRET);
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let parsed = verify(&block, &keys).unwrap();
+        assert_eq!(parsed, config());
+        assert_eq!((parsed.hash_start(), parsed.version_str()), (1001, "2025.08"));
+
+        // A root hash of someone else's choosing does not verify
+        let mut tampered = block.clone();
+        tampered[64] ^= 1;
+        assert_eq!(verify(&tampered, &keys), Err(VerityError::Rejected));
+        assert_eq!(verify(&block, &[ed25519::public_key(&[12; SECRET_KEY_SIZE])]), Err(VerityError::Rejected));
+        assert_eq!(verify(&[0; BLOCK_SIZE], &keys), Err(VerityError::Rejected));
+
+        let mut unsupported = config().encode();
+        unsupported[12] = 2;
+        assert_eq!(BootConfig::decode(&unsupported), Err(VerityError::Malformed));
+    }
+}
@@ -0,0 +1,139 @@
+/*
+ * Orion Operating System - System Image Sealing
+ *
+ * The last step of building a system image: the file system is padded to
+ * whole blocks, its hash tree appended, and the boot configuration with
+ * the root hash signed and put in front. An image sealed this way is
+ * written to a system slot as it is.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::SECRET_KEY_SIZE;
+
+use crate::config::{self, BootConfig, VERSION_SIZE};
+use crate::tree::{self, BlockSource, Geometry, SALT_SIZE};
+use crate::BLOCK_SIZE;
+
+/// File system image, zeros past its end up to the last block
+struct Padded<'a>(&'a [u8]);
+
+impl BlockSource for Padded<'_> {
+    fn read_block(&mut self, index: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        let start = (index as usize * BLOCK_SIZE).min(self.0.len());
+        let end = (start + BLOCK_SIZE).min(self.0.len());
+        buffer.fill(0);
+        buffer[..end - start].copy_from_slice(&self.0[start..end]);
+        Ok(())
+    }
+}
+
+/// Sealed image of the file system image `data`. The salt should be
+/// random and new for every image
+pub fn seal(
+    data: &[u8],
+    version: &[u8; VERSION_SIZE],
+    salt: &[u8; SALT_SIZE],
+    secret_key: &[u8; SECRET_KEY_SIZE],
+) -> Vec<u8> {
+    let data_blocks = data.len().div_ceil(BLOCK_SIZE) as u64;
+    let geometry = Geometry::new(data_blocks);
+    let (tree, root_hash) = match tree::build(&mut Padded(data), 0, &geometry, salt) {
+        Ok(built) => built,
+        Err(_) => unreachable!("padded images always read"),
+    };
+    let config = BootConfig { data_blocks, salt: *salt, root_hash, version: *version };
+
+    let mut image = config::sign(&config, secret_key);
+    image.extend_from_slice(data);
+    image.resize((1 + data_blocks as usize) * BLOCK_SIZE, 0);
+    image.extend_from_slice(&tree);
+    debug_assert_eq!(image.len() as u64, config.image_blocks() * BLOCK_SIZE as u64);
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::tests::MemoryVolume;
+    use crate::tree::Verifier;
+    use crate::VerityError;
+    use alloc::vec;
+    use orion_crypto::ed25519;
+
+    const SECRET: [u8; SECRET_KEY_SIZE] = [5; SECRET_KEY_SIZE];
+    const VERSION: [u8; VERSION_SIZE] = *b"1.4.2\0\0\0\0\0\0\0\0\0\0\0";
+
+    fn sealed(blocks: usize) -> (Vec<u8>, Vec<u8>) {
+        let data: Vec<u8> = (0..blocks * BLOCK_SIZE - 100).map(|byte| (byte % 251) as u8).collect();
+        let image = seal(&data, &VERSION, &[9; SALT_SIZE], &SECRET);
+        (data, image)
+    }
+
+    fn open(image: Vec<u8>) -> Verifier<MemoryVolume> {
+        let config = config::verify(&image[..BLOCK_SIZE], &[ed25519::public_key(&SECRET)]).unwrap();
+        Verifier::new(MemoryVolume(image), &config)
+    }
+
+    #[test]
+    fn test_sealed_images_read_back() {
+        let (data, image) = sealed(300);
+        // Configuration, 300 data blocks, then 1 + 3 hash blocks
+        assert_eq!(image.len(), (1 + 300 + 4) * BLOCK_SIZE);
+
+        let mut verifier = open(image);
+        let mut block = vec![0u8; BLOCK_SIZE];
+        for index in [0, 1, 299, 1, 128] {
+            verifier.read(index, &mut block).unwrap();
+            let start = index as usize * BLOCK_SIZE;
+            let end = (start + BLOCK_SIZE).min(data.len());
+            assert_eq!(block[..end - start], data[start..end]);
+        }
+        assert_eq!(verifier.read(300, &mut block), Err(VerityError::OutOfRange));
+
+        // The top block and the level 0 blocks of 0-127, 128-255 and
+        // 256-299 were read once each
+        let stats = verifier.stats();
+        assert_eq!((stats.verified, stats.hash_reads, stats.corrupt), (5, 4, 0));
+        assert!(stats.cache_hits >= 4);
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let (_, image) = sealed(300);
+        let mut block = vec![0u8; BLOCK_SIZE];
+
+        // A changed data byte fails that block only
+        let mut tampered = image.clone();
+        tampered[(1 + 42) * BLOCK_SIZE + 7] ^= 0x80;
+        let mut verifier = open(tampered);
+        assert_eq!(verifier.read(42, &mut block), Err(VerityError::Corrupt(43)));
+        verifier.read(43, &mut block).unwrap();
+        assert_eq!(verifier.stats().last_corrupt, Some(43));
+
+        // So does a level 0 hash rewritten to match changed data: its hash
+        // block no longer matches the level above
+        let mut tampered = image.clone();
+        let data_block = (1 + 200) * BLOCK_SIZE;
+        tampered[data_block] ^= 1;
+        let digest = tree::hash_block(&[9; SALT_SIZE], &tampered[data_block..data_block + BLOCK_SIZE]);
+        let hash_block = 301 + 2;
+        let at = hash_block * BLOCK_SIZE + (200 - 128) * tree::DIGEST_SIZE;
+        tampered[at..at + tree::DIGEST_SIZE].copy_from_slice(&digest);
+        let mut verifier = open(tampered);
+        assert_eq!(verifier.read(200, &mut block), Err(VerityError::Corrupt(hash_block as u64)));
+        verifier.read(10, &mut block).unwrap();
+
+        // And a tree rebuilt over changed data needs a new signature
+        let mut tampered = image;
+        tampered[64] ^= 1;
+        let keys = [ed25519::public_key(&SECRET)];
+        assert_eq!(config::verify(&tampered[..BLOCK_SIZE], &keys), Err(VerityError::Rejected));
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * Orion Operating System - Verified System Volumes
+ *
+ * A system image is read-only, so any change to it is tampering. Verity
+ * makes that detectable on every read: the image build hashes each 4 KiB
+ * block of the file system, then the hashes a block at a time, up to a
+ * single root hash (tree.rs), and signs that root hash in the boot
+ * configuration at the head of the image (config.rs) with a release key. The block driver checks the signature against the trust
+ * anchors of the I/O server once, then checks every block it reads
+ * against the tree, all the way up to the signed root; a block that does
+ * not match is never handed out.
+ *
+ * A system image, in 4 KiB blocks (image.rs builds it):
+ *
+ *   0          signed boot configuration
+ *   1 .. n     the file system, n data blocks
+ *   n + 1 ..   the hash tree, top level first
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod config;
+pub mod image;
+pub mod tree;
+
+pub use config::{BootConfig, CONFIG_BLOCK, VERSION_SIZE};
+pub use image::seal;
+pub use tree::{BlockSource, Digest, Geometry, Verifier, VerityStats, DIGEST_SIZE, SALT_SIZE};
+
+/// Size of data and hash blocks, and of the boot configuration block
+pub const BLOCK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerityError {
+    /// Reading the volume failed with this status
+    Io(i32),
+    /// Block past the end of the data
+    OutOfRange,
+    /// This block of the volume does not match the hash tree
+    Corrupt(u64),
+    /// Not a boot configuration, or one for other parameters
+    Malformed,
+    /// Unsigned, signed by a key outside the trust anchors, or altered
+    Rejected,
+}
@@ -0,0 +1,291 @@
+/*
+ * Orion Operating System - Verity Hash Tree
+ *
+ * A Merkle tree over the data blocks of a volume. Level 0 holds the
+ * SHA-256 of every data block, 128 to a hash block; each level above
+ * holds the hashes of the blocks of the level below, until a level fits
+ * in one block, whose hash is the root hash. Every hash is taken over the
+ * salt of the image followed by the block, and the last block of a level
+ * is padded with zeros. Levels are stored top first, the layout of
+ * dm-verity, so the blocks read most often sit together.
+ *
+ * Verifier checks a data block by walking up to the first hash block it
+ * checked before, or to the root. Hash blocks it checked are kept, upper
+ * levels longest, so a warm cache costs one hash per block read.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_crypto::sha256::{Sha256, SHA256_DIGEST_SIZE};
+
+use crate::config::BootConfig;
+use crate::{VerityError, BLOCK_SIZE};
+
+pub const DIGEST_SIZE: usize = SHA256_DIGEST_SIZE;
+pub const SALT_SIZE: usize = 32;
+pub const HASHES_PER_BLOCK: usize = BLOCK_SIZE / DIGEST_SIZE;
+
+/// Hash blocks kept once checked, 1 MiB
+pub const CACHED_HASH_BLOCKS: usize = 256;
+
+pub type Digest = [u8; DIGEST_SIZE];
+
+/// The volume a tree covers, in BLOCK_SIZE blocks. Errors are negative
+/// statuses
+pub trait BlockSource {
+    fn read_block(&mut self, index: u64, buffer: &mut [u8]) -> Result<(), i32>;
+}
+
+pub fn hash_block(salt: &[u8; SALT_SIZE], block: &[u8]) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(block);
+    hasher.finalize()
+}
+
+/// Where the levels of the tree over `data_blocks` blocks lie in the hash
+/// area, in blocks from its start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Geometry {
+    pub data_blocks: u64,
+    /// First block and block count of each level, level 0 first
+    levels: Vec<(u64, u64)>,
+}
+
+impl Geometry {
+    pub fn new(data_blocks: u64) -> Self {
+        let mut counts = Vec::new();
+        let mut entries = data_blocks;
+        loop {
+            let blocks = entries.div_ceil(HASHES_PER_BLOCK as u64).max(1);
+            counts.push(blocks);
+            if blocks == 1 {
+                break;
+            }
+            entries = blocks;
+        }
+
+        // Top level first
+        let mut levels = vec![(0, 0); counts.len()];
+        let mut next = 0;
+        for level in (0..counts.len()).rev() {
+            levels[level] = (next, counts[level]);
+            next += counts[level];
+        }
+        Self { data_blocks, levels }
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn hash_blocks(&self) -> u64 {
+        self.levels.iter().map(|&(_, blocks)| blocks).sum()
+    }
+
+    /// Block of the hash area holding block `index` of `level`
+    fn position(&self, level: usize, index: u64) -> u64 {
+        self.levels[level].0 + index
+    }
+}
+
+/// Block of a level holding the hash of entry `index`, a data block for
+/// level 0 and a block of the level below otherwise, with the offset of
+/// that hash in it
+fn locate(index: u64) -> (u64, usize) {
+    let per_block = HASHES_PER_BLOCK as u64;
+    (index / per_block, (index % per_block) as usize * DIGEST_SIZE)
+}
+
+/// Hash tree of the data blocks `source` returns from `first` on, as
+/// stored in the hash area, and its root hash
+pub fn build(
+    source: &mut dyn BlockSource,
+    first: u64,
+    geometry: &Geometry,
+    salt: &[u8; SALT_SIZE],
+) -> Result<(Vec<u8>, Digest), VerityError> {
+    let mut tree = vec![0u8; geometry.hash_blocks() as usize * BLOCK_SIZE];
+    let store = |tree: &mut [u8], level: usize, index: u64, digest: &Digest| {
+        let (block, offset) = locate(index);
+        let at = geometry.position(level, block) as usize * BLOCK_SIZE + offset;
+        tree[at..at + DIGEST_SIZE].copy_from_slice(digest);
+    };
+
+    let mut block = vec![0u8; BLOCK_SIZE];
+    for index in 0..geometry.data_blocks {
+        source.read_block(first + index, &mut block).map_err(VerityError::Io)?;
+        store(&mut tree, 0, index, &hash_block(salt, &block));
+    }
+    for level in 1..geometry.levels() {
+        for index in 0..geometry.levels[level - 1].1 {
+            let at = geometry.position(level - 1, index) as usize * BLOCK_SIZE;
+            let digest = hash_block(salt, &tree[at..at + BLOCK_SIZE]);
+            store(&mut tree, level, index, &digest);
+        }
+    }
+
+    // The top level is the first block
+    let root = hash_block(salt, &tree[..BLOCK_SIZE]);
+    Ok((tree, root))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VerityStats {
+    /// Data blocks read and found intact
+    pub verified: u64,
+    /// Hash blocks read from the volume
+    pub hash_reads: u64,
+    /// Hash blocks found among those checked before
+    pub cache_hits: u64,
+    /// Blocks that did not match the tree
+    pub corrupt: u64,
+    /// The last of them, in volume blocks
+    pub last_corrupt: Option<u64>,
+}
+
+/// Reads the data blocks of a volume described by a boot configuration
+/// whose signature was checked, each against the tree
+pub struct Verifier<S: BlockSource> {
+    source: S,
+    geometry: Geometry,
+    salt: [u8; SALT_SIZE],
+    root: Digest,
+    data_start: u64,
+    hash_start: u64,
+    /// Checked hash blocks by level and index; the lowest levels go first
+    cache: BTreeMap<(usize, u64), Vec<u8>>,
+    stats: VerityStats,
+}
+
+impl<S: BlockSource> Verifier<S> {
+    pub fn new(source: S, config: &BootConfig) -> Self {
+        Self {
+            source,
+            geometry: config.geometry(),
+            salt: config.salt,
+            root: config.root_hash,
+            data_start: config.data_start(),
+            hash_start: config.hash_start(),
+            cache: BTreeMap::new(),
+            stats: VerityStats::default(),
+        }
+    }
+
+    pub fn data_blocks(&self) -> u64 {
+        self.geometry.data_blocks
+    }
+
+    pub fn stats(&self) -> VerityStats {
+        self.stats
+    }
+
+    /// Fill `buffer`, BLOCK_SIZE bytes, with data block `index`
+    pub fn read(&mut self, index: u64, buffer: &mut [u8]) -> Result<(), VerityError> {
+        if index >= self.geometry.data_blocks {
+            return Err(VerityError::OutOfRange);
+        }
+        self.source.read_block(self.data_start + index, buffer).map_err(VerityError::Io)?;
+        let expected = self.expected(0, index)?;
+        if hash_block(&self.salt, buffer) != expected {
+            return Err(self.corrupt(self.data_start + index));
+        }
+        self.stats.verified += 1;
+        Ok(())
+    }
+
+    /// Hash of entry `index` of `level`, out of a checked hash block
+    fn expected(&mut self, level: usize, index: u64) -> Result<Digest, VerityError> {
+        let (block, offset) = locate(index);
+        self.load(level, block)?;
+        let mut digest = [0u8; DIGEST_SIZE];
+        digest.copy_from_slice(&self.cache[&(level, block)][offset..offset + DIGEST_SIZE]);
+        Ok(digest)
+    }
+
+    /// Read and check hash block `index` of `level` unless it was already
+    fn load(&mut self, level: usize, index: u64) -> Result<(), VerityError> {
+        if self.cache.contains_key(&(level, index)) {
+            self.stats.cache_hits += 1;
+            return Ok(());
+        }
+        let volume_block = self.hash_start + self.geometry.position(level, index);
+        let mut block = vec![0u8; BLOCK_SIZE];
+        self.source.read_block(volume_block, &mut block).map_err(VerityError::Io)?;
+        self.stats.hash_reads += 1;
+
+        let expected = if level + 1 == self.geometry.levels() { self.root } else { self.expected(level + 1, index)? };
+        if hash_block(&self.salt, &block) != expected {
+            return Err(self.corrupt(volume_block));
+        }
+        if self.cache.len() >= CACHED_HASH_BLOCKS {
+            self.cache.pop_first();
+        }
+        self.cache.insert((level, index), block);
+        Ok(())
+    }
+
+    fn corrupt(&mut self, volume_block: u64) -> VerityError {
+        self.stats.corrupt += 1;
+        self.stats.last_corrupt = Some(volume_block);
+        VerityError::Corrupt(volume_block)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Volume kept in memory
+    pub struct MemoryVolume(pub Vec<u8>);
+
+    impl BlockSource for MemoryVolume {
+        fn read_block(&mut self, index: u64, buffer: &mut [u8]) -> Result<(), i32> {
+            let start = index as usize * BLOCK_SIZE;
+            buffer.copy_from_slice(self.0.get(start..start + BLOCK_SIZE).ok_or(-5)?);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lays_levels_out_top_first() {
+        let small = Geometry::new(3);
+        assert_eq!((small.levels(), small.hash_blocks()), (1, 1));
+
+        // 128 * 128 + 1 data blocks: 129 level 0 blocks, 2 above, then 1
+        let large = Geometry::new(128 * 128 + 1);
+        assert_eq!(large.levels, [(3, 129), (1, 2), (0, 1)]);
+        assert_eq!(locate(128 * 3 + 5), (3, 5 * DIGEST_SIZE));
+        assert_eq!(large.position(1, 1), 2);
+    }
+
+    #[test]
+    fn test_tree_hashes_every_level() {
+        let salt = [3; SALT_SIZE];
+        let data: Vec<u8> = (0..130 * BLOCK_SIZE).map(|byte| (byte / BLOCK_SIZE) as u8).collect();
+        let geometry = Geometry::new(130);
+        let (tree, root) = build(&mut MemoryVolume(data.clone()), 0, &geometry, &salt).unwrap();
+        assert_eq!(tree.len(), 3 * BLOCK_SIZE);
+
+        // Level 0 starts after the top block; the last hash lands in its
+        // second block, after the one of block 128
+        let level0 = &tree[BLOCK_SIZE..];
+        assert_eq!(level0[..DIGEST_SIZE], hash_block(&salt, &data[..BLOCK_SIZE]));
+        let last = BLOCK_SIZE + DIGEST_SIZE;
+        assert_eq!(level0[last..last + DIGEST_SIZE], hash_block(&salt, &data[129 * BLOCK_SIZE..]));
+        assert_eq!(tree[DIGEST_SIZE..2 * DIGEST_SIZE], hash_block(&salt, &level0[BLOCK_SIZE..2 * BLOCK_SIZE]));
+        assert_eq!(root, hash_block(&salt, &tree[..BLOCK_SIZE]));
+
+        // The salt changes every hash
+        let (_, other) = build(&mut MemoryVolume(data), 0, &geometry, &[4; SALT_SIZE]).unwrap();
+        assert_ne!(root, other);
+    }
+}
@@ -0,0 +1,202 @@
+/*
+ * Orion Operating System - Virtqueue Descriptor Table
+ *
+ * Free descriptors of one queue, handed out as chains linked through
+ * their next field. A chain goes back to the free list when its guard is
+ * dropped, whether the request completed, timed out or was never
+ * submitted. The guard must outlive the request on the device: a driver
+ * waiting for completion keeps it until the used ring returns its head.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::panic::Location;
+
+use crate::leak::{HandleKind, LeakTracker};
+
+/// The descriptor continues in `next`
+pub const VIRTQ_DESC_F_NEXT: u16 = 1;
+/// The device writes the buffer
+pub const VIRTQ_DESC_F_WRITE: u16 = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VirtqDesc {
+    pub addr: u64,
+    pub len: u32,
+    pub flags: u16,
+    pub next: u16,
+}
+
+pub struct DescTable {
+    queue: u16,
+    desc: *mut VirtqDesc,
+    size: u16,
+    free: RefCell<Vec<u16>>,
+    outstanding: Cell<u16>,
+    tracker: Rc<LeakTracker>,
+}
+
+impl DescTable {
+    /// Table of `size` descriptors at `desc`, all free
+    ///
+    /// # Safety
+    /// `desc` must point to `size` descriptors owned by the queue for the
+    /// lifetime of the table.
+    pub unsafe fn new(queue: u16, desc: *mut VirtqDesc, size: u16, tracker: Rc<LeakTracker>) -> Self {
+        Self { queue, desc, size, free: RefCell::new((0..size).rev().collect()), outstanding: Cell::new(0), tracker }
+    }
+
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    pub fn num_free(&self) -> u16 {
+        self.free.borrow().len() as u16
+    }
+
+    /// Chains handed out and not dropped yet
+    pub fn outstanding(&self) -> u16 {
+        self.outstanding.get()
+    }
+
+    /// Descriptor `index` as last written, e.g. the head the used ring
+    /// returned
+    pub fn read(&self, index: u16) -> VirtqDesc {
+        assert!(index < self.size, "descriptor {} beyond table of {}", index, self.size);
+        unsafe { *self.desc.add(index as usize) }
+    }
+
+    /// Take `count` linked descriptors; None when fewer are free
+    #[track_caller]
+    pub fn alloc_chain(&self, count: u16) -> Option<DescChain<'_>> {
+        let location = Location::caller();
+        let mut free = self.free.borrow_mut();
+        if count == 0 || free.len() < count as usize {
+            return None;
+        }
+        let indices: Vec<u16> = (0..count).filter_map(|_| free.pop()).collect();
+        for (position, &index) in indices.iter().enumerate() {
+            let next = indices.get(position + 1).copied();
+            let desc = VirtqDesc {
+                flags: if next.is_some() { VIRTQ_DESC_F_NEXT } else { 0 },
+                next: next.unwrap_or(0),
+                ..VirtqDesc::default()
+            };
+            unsafe { self.desc.add(index as usize).write(desc) };
+        }
+        let head = indices[0];
+        self.outstanding.set(self.outstanding.get() + 1);
+        let id = self.tracker.track(HandleKind::Descriptor { queue: self.queue, head, count }, location);
+        Some(DescChain { table: self, head, count, id })
+    }
+
+    fn release(&self, head: u16, count: u16, id: Option<u64>) {
+        let mut free = self.free.borrow_mut();
+        let mut index = head;
+        for _ in 0..count {
+            free.push(index);
+            index = unsafe { (*self.desc.add(index as usize)).next };
+        }
+        self.outstanding.set(self.outstanding.get() - 1);
+        self.tracker.untrack(id);
+    }
+}
+
+/// Descriptors of one request, given back on drop
+pub struct DescChain<'a> {
+    table: &'a DescTable,
+    head: u16,
+    count: u16,
+    id: Option<u64>,
+}
+
+impl DescChain<'_> {
+    /// Index to place in the available ring, and returned by the device
+    pub fn head(&self) -> u16 {
+        self.head
+    }
+
+    pub fn len(&self) -> u16 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn index(&self, position: u16) -> u16 {
+        assert!(position < self.count, "descriptor {} beyond chain of {}", position, self.count);
+        let mut index = self.head;
+        for _ in 0..position {
+            index = unsafe { (*self.table.desc.add(index as usize)).next };
+        }
+        index
+    }
+
+    /// Point descriptor `position` of the chain at a buffer. `flags` may
+    /// hold VIRTQ_DESC_F_WRITE; the link to the next descriptor is kept
+    pub fn set(&self, position: u16, addr: u64, len: u32, flags: u16) {
+        let desc = unsafe { &mut *self.table.desc.add(self.index(position) as usize) };
+        desc.addr = addr;
+        desc.len = len;
+        desc.flags = (flags & !VIRTQ_DESC_F_NEXT) | (desc.flags & VIRTQ_DESC_F_NEXT);
+    }
+
+    pub fn get(&self, position: u16) -> VirtqDesc {
+        unsafe { *self.table.desc.add(self.index(position) as usize) }
+    }
+}
+
+impl Drop for DescChain<'_> {
+    fn drop(&mut self) {
+        self.table.release(self.head, self.count, self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_chains_are_linked_and_freed_on_drop() {
+        let mut descs = vec![VirtqDesc::default(); 4];
+        let tracker = Rc::new(LeakTracker::new("test"));
+        tracker.set_enabled(true);
+        let table = unsafe { DescTable::new(0, descs.as_mut_ptr(), 4, tracker.clone()) };
+
+        let chain = table.alloc_chain(3).unwrap();
+        assert_eq!(table.num_free(), 1);
+        assert!(table.alloc_chain(2).is_none());
+        chain.set(0, 0x1000, 24, 0);
+        chain.set(2, 0x2000, 8, VIRTQ_DESC_F_WRITE);
+        let first = chain.get(0);
+        assert_eq!(first.flags, VIRTQ_DESC_F_NEXT);
+        assert_eq!(chain.get(1).flags & VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_NEXT);
+        assert_eq!(chain.get(2), VirtqDesc { addr: 0x2000, len: 8, flags: VIRTQ_DESC_F_WRITE, next: 0 });
+        assert_eq!(tracker.outstanding(), 1);
+
+        // Leaving early, as on a timeout, gives every descriptor back
+        let early = || -> Option<()> {
+            let _chain = table.alloc_chain(1)?;
+            None
+        };
+        assert!(early().is_none());
+        assert_eq!(table.num_free(), 1);
+
+        drop(chain);
+        assert_eq!(table.num_free(), 4);
+        assert_eq!(table.outstanding(), 0);
+        assert_eq!(tracker.outstanding(), 0);
+        let all = table.alloc_chain(4).unwrap();
+        assert_eq!(all.len(), 4);
+    }
+}
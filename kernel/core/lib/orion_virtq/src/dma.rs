@@ -0,0 +1,221 @@
+/*
+ * Orion Operating System - DMA Buffers
+ *
+ * Buffers shared with a device, taken from a pool and given back when
+ * their guard is dropped. The pool sits on any allocator of bus
+ * addresses; DmaRegion manages a fixed window first fit, merging
+ * neighbouring free ranges. Addresses are identity mapped, so a buffer's
+ * address is also where the driver reads and writes it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::panic::Location;
+
+use crate::leak::{HandleKind, LeakTracker};
+
+pub trait DmaAllocator {
+    /// `size` bytes aligned to `align`, a power of two
+    fn alloc(&self, size: usize, align: usize) -> Option<u64>;
+    fn free(&self, addr: u64, size: usize);
+}
+
+fn round_up(value: u64, align: u64) -> u64 {
+    (value + align - 1) & !(align - 1)
+}
+
+/// Fixed window of bus addresses
+pub struct DmaRegion {
+    /// Free ranges as (start, end), sorted and never adjacent
+    free: RefCell<Vec<(u64, u64)>>,
+}
+
+impl DmaRegion {
+    pub fn new(base: u64, size: u64) -> Self {
+        let mut free = Vec::new();
+        if size != 0 {
+            free.push((base, base + size));
+        }
+        Self { free: RefCell::new(free) }
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.free.borrow().iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// Size of the largest free range, the biggest allocation possible
+    /// without alignment padding
+    pub fn largest_free(&self) -> u64 {
+        self.free.borrow().iter().map(|(start, end)| end - start).max().unwrap_or(0)
+    }
+
+    /// Number of free ranges the free space is split into
+    pub fn free_ranges(&self) -> usize {
+        self.free.borrow().len()
+    }
+}
+
+impl DmaAllocator for DmaRegion {
+    fn alloc(&self, size: usize, align: usize) -> Option<u64> {
+        let size = size.max(1) as u64;
+        let mut free = self.free.borrow_mut();
+        let position = free.iter().position(|&(start, end)| round_up(start, align as u64) + size <= end)?;
+        let (start, end) = free[position];
+        let addr = round_up(start, align as u64);
+        free.remove(position);
+        if addr + size < end {
+            free.insert(position, (addr + size, end));
+        }
+        if start < addr {
+            free.insert(position, (start, addr));
+        }
+        Some(addr)
+    }
+
+    fn free(&self, addr: u64, size: usize) {
+        let end = addr + size.max(1) as u64;
+        let mut free = self.free.borrow_mut();
+        let position = free.partition_point(|&(start, _)| start < addr);
+        let merges_next = position < free.len() && free[position].0 == end;
+        let merges_prev = position > 0 && free[position - 1].1 == addr;
+        match (merges_prev, merges_next) {
+            (true, true) => {
+                free[position - 1].1 = free[position].1;
+                free.remove(position);
+            }
+            (true, false) => free[position - 1].1 = end,
+            (false, true) => free[position].0 = addr,
+            (false, false) => free.insert(position, (addr, end)),
+        }
+    }
+}
+
+pub struct DmaPool<A: DmaAllocator> {
+    allocator: A,
+    align: usize,
+    tracker: Rc<LeakTracker>,
+}
+
+impl<A: DmaAllocator> DmaPool<A> {
+    /// Pool handing out buffers aligned to `align` from `allocator`
+    pub fn new(allocator: A, align: usize, tracker: Rc<LeakTracker>) -> Self {
+        Self { allocator, align, tracker }
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.allocator
+    }
+
+    /// Zeroed buffer of `size` bytes; None when the allocator is exhausted
+    #[track_caller]
+    pub fn alloc(&self, size: usize) -> Option<DmaBuffer<'_, A>> {
+        let location = Location::caller();
+        let addr = self.allocator.alloc(size, self.align)?;
+        unsafe { core::ptr::write_bytes(addr as *mut u8, 0, size) };
+        let id = self.tracker.track(HandleKind::DmaBuffer { addr, size }, location);
+        Some(DmaBuffer { pool: self, addr, size, id })
+    }
+}
+
+/// Memory shared with a device, given back on drop
+pub struct DmaBuffer<'a, A: DmaAllocator> {
+    pool: &'a DmaPool<A>,
+    addr: u64,
+    size: usize,
+    id: Option<u64>,
+}
+
+impl<A: DmaAllocator> DmaBuffer<'_, A> {
+    /// Address to place in a descriptor
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.addr as *mut u8
+    }
+
+    /// Copy `bytes` into the buffer at `offset`
+    pub fn write(&self, offset: usize, bytes: &[u8]) {
+        assert!(offset + bytes.len() <= self.size, "write beyond DMA buffer");
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.as_ptr().add(offset), bytes.len()) };
+    }
+
+    /// Copy `bytes.len()` bytes out of the buffer at `offset`
+    pub fn read(&self, offset: usize, bytes: &mut [u8]) {
+        assert!(offset + bytes.len() <= self.size, "read beyond DMA buffer");
+        unsafe { core::ptr::copy_nonoverlapping(self.as_ptr().add(offset), bytes.as_mut_ptr(), bytes.len()) };
+    }
+}
+
+impl<A: DmaAllocator> Drop for DmaBuffer<'_, A> {
+    fn drop(&mut self) {
+        self.pool.allocator.free(self.addr, self.size);
+        self.pool.tracker.untrack(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::alloc::{alloc, dealloc, Layout};
+
+    #[test]
+    fn test_region_splits_and_merges() {
+        let region = DmaRegion::new(0x1000, 0x4000);
+        let a = region.alloc(0x100, 0x1000).unwrap();
+        let b = region.alloc(0x100, 0x1000).unwrap();
+        assert_eq!((a, b), (0x1000, 0x2000));
+        assert_eq!(region.free_bytes(), 0x4000 - 0x200);
+        assert!(region.alloc(0x3000, 0x1000).is_none());
+
+        region.free(a, 0x100);
+        assert_eq!((region.free_ranges(), region.largest_free()), (2, 0x4000 - 0x1100));
+        region.free(b, 0x100);
+        assert_eq!(region.free_bytes(), 0x4000);
+        assert_eq!((region.free_ranges(), region.largest_free()), (1, 0x4000));
+        assert_eq!(region.alloc(0x4000, 0x1000), Some(0x1000));
+    }
+
+    #[test]
+    fn test_buffers_are_freed_on_drop_and_leaks_reported() {
+        let layout = Layout::from_size_align(0x2000, 0x1000).unwrap();
+        let arena = unsafe { alloc(layout) };
+        let tracker = Rc::new(LeakTracker::new("test"));
+        tracker.set_enabled(true);
+        let pool = DmaPool::new(DmaRegion::new(arena as u64, 0x2000), 64, tracker.clone());
+
+        let buffer = pool.alloc(48).unwrap();
+        buffer.write(8, &[1, 2, 3]);
+        let mut bytes = [0u8; 4];
+        buffer.read(7, &mut bytes);
+        assert_eq!(bytes, [0, 1, 2, 3]);
+        drop(buffer);
+        assert_eq!(pool.allocator().free_bytes(), 0x2000);
+
+        // A forgotten buffer shows in the report with where it was taken
+        core::mem::forget(pool.alloc(100).unwrap());
+        let report = tracker.report();
+        assert_eq!(report.handles.len(), 1);
+        assert_eq!(report.handles[0].kind, HandleKind::DmaBuffer { addr: arena as u64, size: 100 });
+        assert_eq!(report.handles[0].location.file(), file!());
+
+        // Nothing is recorded while tracking is off
+        tracker.set_enabled(false);
+        drop(pool.alloc(16).unwrap());
+        assert_eq!(tracker.outstanding(), 1);
+
+        unsafe { dealloc(arena, layout) };
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * Orion Operating System - Handle Leak Tracking
+ *
+ * Outstanding descriptor chains and DMA buffers of one driver, each with
+ * the source location that took it. Tracking is on in debug builds and
+ * can be switched at run time; handles taken while it is off are never
+ * listed.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::panic::Location;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleKind {
+    /// Chain of `count` descriptors starting at `head` on queue `queue`
+    Descriptor {
+        queue: u16,
+        head: u16,
+        count: u16,
+    },
+    DmaBuffer {
+        addr: u64,
+        size: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle {
+    pub kind: HandleKind,
+    /// Where the handle was taken
+    pub location: &'static Location<'static>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakReport {
+    pub driver: &'static str,
+    /// Oldest first
+    pub handles: Vec<Handle>,
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+}
+
+impl fmt::Display for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            HandleKind::Descriptor { queue, head, count } => {
+                write!(f, "{} descriptor(s) at {} on queue {}", count, head, queue)?
+            }
+            HandleKind::DmaBuffer { addr, size } => write!(f, "DMA buffer of {} bytes at {:#x}", size, addr)?,
+        }
+        write!(f, ", taken at {}:{}", self.location.file(), self.location.line())
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} outstanding handle(s)", self.driver, self.handles.len())?;
+        for handle in &self.handles {
+            write!(f, "\n  {}", handle)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct LeakTracker {
+    driver: &'static str,
+    enabled: Cell<bool>,
+    next_id: Cell<u64>,
+    outstanding: RefCell<BTreeMap<u64, Handle>>,
+}
+
+impl LeakTracker {
+    pub fn new(driver: &'static str) -> Self {
+        Self {
+            driver,
+            enabled: Cell::new(cfg!(debug_assertions)),
+            next_id: Cell::new(0),
+            outstanding: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn driver(&self) -> &'static str {
+        self.driver
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    /// Record a handle; the id to untrack it with, None when tracking is off
+    pub(crate) fn track(&self, kind: HandleKind, location: &'static Location<'static>) -> Option<u64> {
+        if !self.enabled.get() {
+            return None;
+        }
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.outstanding.borrow_mut().insert(id, Handle { kind, location });
+        Some(id)
+    }
+
+    pub(crate) fn untrack(&self, id: Option<u64>) {
+        if let Some(id) = id {
+            self.outstanding.borrow_mut().remove(&id);
+        }
+    }
+
+    /// Handles taken and not released yet
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.borrow().len()
+    }
+
+    pub fn report(&self) -> LeakReport {
+        LeakReport { driver: self.driver, handles: self.outstanding.borrow().values().copied().collect() }
+    }
+}
@@ -0,0 +1,32 @@
+/*
+ * Orion Operating System - Virtqueue Handles
+ *
+ * Descriptor chains and DMA buffers handed out as guards that give their
+ * descriptors or memory back when dropped, so that a driver returning
+ * early on error, a timeout for instance, cannot leak them. In debug
+ * builds every outstanding handle is recorded with the place it was
+ * taken, and the tracker of a driver lists the handles still held, which
+ * at shutdown are leaks.
+ *
+ *     let chain = queue.alloc_chain(1).ok_or(DriverError::General)?;
+ *     let buffer = pool.alloc(size).ok_or(DriverError::OutOfMemory)?;
+ *     chain.set(0, buffer.addr(), size as u32, 0);
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod desc;
+pub mod dma;
+pub mod leak;
+
+pub use desc::{DescChain, DescTable, VirtqDesc, VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
+pub use dma::{DmaAllocator, DmaBuffer, DmaPool, DmaRegion};
+pub use leak::{Handle, HandleKind, LeakReport, LeakTracker};
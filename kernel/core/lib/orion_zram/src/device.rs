@@ -0,0 +1,440 @@
+/*
+ * Orion Operating System - Compressed RAM Device
+ *
+ * The device is a table with one slot per page. A page written to it is
+ * checked for a single repeated 64-bit word first; such a page, which is
+ * what most freshly swapped out memory looks like when it is zero, only
+ * records the word. Anything else is compressed with LZ4 and kept as a
+ * blob of the compressed size, unless the result is larger than three
+ * quarters of a page, in which case the page is stored raw: decompressing
+ * it would cost time for a saving that is not there.
+ *
+ * I/O is in 512 byte sectors. Writes smaller than a page read the page
+ * back, patch it and store it again, which is fine for tmpfs metadata;
+ * swap only ever writes whole pages. Discarded pages free their slot, and
+ * an optional memory limit makes writes fail instead of growing the
+ * device past what the system is willing to spend on it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::lz4;
+
+pub const PAGE_SIZE: usize = 4096;
+pub const SECTOR_SIZE: usize = 512;
+/// Compressed pages larger than this are stored uncompressed
+pub const MAX_COMPRESSED_SIZE: usize = PAGE_SIZE * 3 / 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZramError {
+    /// The request reaches past the end of the device
+    OutOfRange,
+    /// Offset or length is not a multiple of the sector size
+    Unaligned,
+    /// Storing the page would exceed the memory limit
+    MemoryLimit,
+    /// A stored page no longer decompresses to a full page
+    Corrupt,
+}
+
+enum Slot {
+    Empty,
+    /// Every word of the page holds this value
+    Same(u64),
+    Compressed(Box<[u8]>),
+    /// Did not compress well enough, kept as written
+    Raw(Box<[u8]>),
+}
+
+impl Slot {
+    fn stored_bytes(&self) -> u64 {
+        match self {
+            Slot::Compressed(blob) | Slot::Raw(blob) => blob.len() as u64,
+            Slot::Empty | Slot::Same(_) => 0,
+        }
+    }
+}
+
+/// What the device holds and what it costs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZramStats {
+    /// Size of the device in bytes
+    pub disk_size: u64,
+    /// Pages holding data, however they are stored
+    pub pages_stored: u64,
+    /// Pages that are all zero
+    pub zero_pages: u64,
+    /// Pages filled with one non-zero word
+    pub same_pages: u64,
+    /// Pages stored uncompressed
+    pub huge_pages: u64,
+    /// Bytes of data held, before compression
+    pub orig_data_size: u64,
+    /// Bytes of compressed and raw page storage
+    pub compr_data_size: u64,
+    /// Memory used by the device: page storage plus the slot table
+    pub mem_used: u64,
+    /// Memory limit in bytes, 0 for none
+    pub mem_limit: u64,
+    pub reads: u64,
+    pub writes: u64,
+    /// Pages freed by discards
+    pub discarded_pages: u64,
+    /// Writes refused by the memory limit
+    pub failed_writes: u64,
+}
+
+impl ZramStats {
+    /// Data held per byte of page storage; pages that cost nothing make
+    /// this grow without bound, so it reads 0 with nothing stored
+    pub fn compression_ratio(&self) -> f64 {
+        if self.compr_data_size == 0 {
+            return 0.0;
+        }
+        self.orig_data_size as f64 / self.compr_data_size as f64
+    }
+
+    /// Memory the device saves over keeping the data uncompressed
+    pub fn memory_saved(&self) -> u64 {
+        self.orig_data_size.saturating_sub(self.mem_used)
+    }
+}
+
+pub struct Zram {
+    slots: Vec<Slot>,
+    mem_limit: u64,
+    stats: ZramStats,
+}
+
+/// The fill word, if the page is one word repeated
+fn same_filled(page: &[u8]) -> Option<u64> {
+    let mut words = page.chunks_exact(8).map(|word| u64::from_le_bytes(word.try_into().unwrap()));
+    let first = words.next()?;
+    words.all(|word| word == first).then_some(first)
+}
+
+impl Zram {
+    /// A device of `disk_size` bytes, rounded up to whole pages
+    pub fn new(disk_size: u64) -> Self {
+        let pages = disk_size.div_ceil(PAGE_SIZE as u64) as usize;
+        let mut slots = Vec::with_capacity(pages);
+        slots.resize_with(pages, || Slot::Empty);
+        Self {
+            slots,
+            mem_limit: 0,
+            stats: ZramStats { disk_size: (pages * PAGE_SIZE) as u64, ..ZramStats::default() },
+        }
+    }
+
+    pub fn disk_size(&self) -> u64 {
+        self.stats.disk_size
+    }
+
+    /// Cap the memory used by page storage, 0 to remove the cap. Pages
+    /// already stored stay; only new writes are refused
+    pub fn set_mem_limit(&mut self, bytes: u64) {
+        self.mem_limit = bytes;
+    }
+
+    pub fn stats(&self) -> ZramStats {
+        let table = (self.slots.len() * mem::size_of::<Slot>()) as u64;
+        ZramStats {
+            mem_used: self.stats.compr_data_size + table,
+            mem_limit: self.mem_limit,
+            ..self.stats
+        }
+    }
+
+    fn check_range(&self, offset: u64, length: u64) -> Result<(), ZramError> {
+        if !offset.is_multiple_of(SECTOR_SIZE as u64) || !length.is_multiple_of(SECTOR_SIZE as u64) {
+            return Err(ZramError::Unaligned);
+        }
+        match offset.checked_add(length) {
+            Some(end) if end <= self.disk_size() => Ok(()),
+            _ => Err(ZramError::OutOfRange),
+        }
+    }
+
+    fn read_page(&self, index: usize, page: &mut [u8]) -> Result<(), ZramError> {
+        match &self.slots[index] {
+            Slot::Empty => page.fill(0),
+            Slot::Same(word) => {
+                for chunk in page.chunks_exact_mut(8) {
+                    chunk.copy_from_slice(&word.to_le_bytes());
+                }
+            }
+            Slot::Raw(blob) => page.copy_from_slice(blob),
+            Slot::Compressed(blob) => {
+                let data = lz4::decompress(blob, PAGE_SIZE).map_err(|_| ZramError::Corrupt)?;
+                if data.len() != PAGE_SIZE {
+                    return Err(ZramError::Corrupt);
+                }
+                page.copy_from_slice(&data);
+            }
+        }
+        Ok(())
+    }
+
+    fn free_slot(&mut self, index: usize) {
+        let slot = mem::replace(&mut self.slots[index], Slot::Empty);
+        let stats = &mut self.stats;
+        match slot {
+            Slot::Empty => return,
+            Slot::Same(0) => stats.zero_pages -= 1,
+            Slot::Same(_) => stats.same_pages -= 1,
+            Slot::Raw(_) => stats.huge_pages -= 1,
+            Slot::Compressed(_) => {}
+        }
+        stats.compr_data_size -= slot.stored_bytes();
+        stats.orig_data_size -= PAGE_SIZE as u64;
+        stats.pages_stored -= 1;
+    }
+
+    fn write_page(&mut self, index: usize, page: &[u8]) -> Result<(), ZramError> {
+        let slot = match same_filled(page) {
+            Some(word) => Slot::Same(word),
+            None => {
+                let compressed = lz4::compress(page);
+                if compressed.len() > MAX_COMPRESSED_SIZE {
+                    Slot::Raw(page.into())
+                } else {
+                    Slot::Compressed(compressed.into_boxed_slice())
+                }
+            }
+        };
+
+        if self.mem_limit != 0 {
+            let used = self.stats.compr_data_size - self.slots[index].stored_bytes() + slot.stored_bytes();
+            if used > self.mem_limit {
+                self.stats.failed_writes += 1;
+                return Err(ZramError::MemoryLimit);
+            }
+        }
+
+        self.free_slot(index);
+        let stats = &mut self.stats;
+        match slot {
+            Slot::Same(0) => stats.zero_pages += 1,
+            Slot::Same(_) => stats.same_pages += 1,
+            Slot::Raw(_) => stats.huge_pages += 1,
+            Slot::Compressed(_) | Slot::Empty => {}
+        }
+        stats.compr_data_size += slot.stored_bytes();
+        stats.orig_data_size += PAGE_SIZE as u64;
+        stats.pages_stored += 1;
+        self.slots[index] = slot;
+        Ok(())
+    }
+
+    /// Read `buffer.len()` bytes at `offset`; both must be sector aligned.
+    /// Sectors never written read as zeros
+    pub fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), ZramError> {
+        self.check_range(offset, buffer.len() as u64)?;
+        let mut page = [0u8; PAGE_SIZE];
+        let mut done = 0;
+        while done < buffer.len() {
+            let position = offset as usize + done;
+            let within = position % PAGE_SIZE;
+            let count = (PAGE_SIZE - within).min(buffer.len() - done);
+            self.read_page(position / PAGE_SIZE, &mut page)?;
+            buffer[done..done + count].copy_from_slice(&page[within..within + count]);
+            done += count;
+        }
+        self.stats.reads += 1;
+        Ok(())
+    }
+
+    /// Write `data` at `offset`; both must be sector aligned. A failure
+    /// part way leaves the pages before it written
+    pub fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), ZramError> {
+        self.check_range(offset, data.len() as u64)?;
+        let mut page = [0u8; PAGE_SIZE];
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset as usize + done;
+            let index = position / PAGE_SIZE;
+            let within = position % PAGE_SIZE;
+            let count = (PAGE_SIZE - within).min(data.len() - done);
+            if count == PAGE_SIZE {
+                self.write_page(index, &data[done..done + count])?;
+            } else {
+                self.read_page(index, &mut page)?;
+                page[within..within + count].copy_from_slice(&data[done..done + count]);
+                self.write_page(index, &page)?;
+            }
+            done += count;
+        }
+        self.stats.writes += 1;
+        Ok(())
+    }
+
+    /// Forget the pages entirely inside [offset, offset + length); partial
+    /// pages at either end keep their data. Returns the pages freed
+    pub fn discard(&mut self, offset: u64, length: u64) -> Result<u64, ZramError> {
+        self.check_range(offset, length)?;
+        let page = PAGE_SIZE as u64;
+        let first = offset.div_ceil(page) as usize;
+        let last = ((offset + length) / page) as usize;
+        let mut freed = 0;
+        for index in first..last.max(first) {
+            if !matches!(self.slots[index], Slot::Empty) {
+                self.free_slot(index);
+                freed += 1;
+            }
+        }
+        self.stats.discarded_pages += freed;
+        Ok(freed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn text_page(seed: u8) -> Vec<u8> {
+        let line = b"swap slot 0000 holds a page of an idle process; ";
+        let mut page: Vec<u8> = line.iter().cycle().take(PAGE_SIZE).copied().collect();
+        page[10] = seed;
+        page
+    }
+
+    fn noise_page(mut state: u32) -> Vec<u8> {
+        (0..PAGE_SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn read_page(zram: &mut Zram, index: u64) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE_SIZE];
+        zram.read(index * PAGE_SIZE as u64, &mut page).unwrap();
+        page
+    }
+
+    #[test]
+    fn test_pages_read_back_as_written() {
+        let mut zram = Zram::new(MIB);
+        let text = text_page(1);
+        let noise = noise_page(99);
+        zram.write(0, &text).unwrap();
+        zram.write(PAGE_SIZE as u64, &noise).unwrap();
+
+        assert_eq!(read_page(&mut zram, 0), text);
+        assert_eq!(read_page(&mut zram, 1), noise);
+        assert_eq!(read_page(&mut zram, 2), vec![0u8; PAGE_SIZE]);
+
+        let stats = zram.stats();
+        assert_eq!(stats.pages_stored, 2);
+        assert_eq!(stats.huge_pages, 1);
+        assert_eq!(stats.compr_data_size - PAGE_SIZE as u64, lz4::compress(&text).len() as u64);
+    }
+
+    #[test]
+    fn test_same_filled_pages_take_no_storage() {
+        let mut zram = Zram::new(MIB);
+        zram.write(0, &[0u8; PAGE_SIZE]).unwrap();
+        let filled: Vec<u8> = 0xdead_beef_0bad_f00du64.to_le_bytes().iter().cycle().take(PAGE_SIZE).copied().collect();
+        zram.write(PAGE_SIZE as u64, &filled).unwrap();
+
+        let stats = zram.stats();
+        assert_eq!((stats.zero_pages, stats.same_pages, stats.pages_stored), (1, 1, 2));
+        assert_eq!(stats.compr_data_size, 0);
+        assert_eq!(read_page(&mut zram, 1), filled);
+    }
+
+    #[test]
+    fn test_overwriting_and_discarding_release_storage() {
+        let mut zram = Zram::new(MIB);
+        zram.write(0, &noise_page(5)).unwrap();
+        zram.write(0, &text_page(2)).unwrap();
+        let stats = zram.stats();
+        assert_eq!((stats.pages_stored, stats.huge_pages), (1, 0));
+        assert_eq!(stats.orig_data_size, PAGE_SIZE as u64);
+
+        zram.write(PAGE_SIZE as u64, &text_page(3)).unwrap();
+        // Only the first page lies entirely inside the range
+        assert_eq!(zram.discard(0, PAGE_SIZE as u64 + 1024), Ok(1));
+        let stats = zram.stats();
+        assert_eq!(stats.pages_stored, 1);
+        assert_eq!(stats.discarded_pages, 1);
+        assert_eq!(read_page(&mut zram, 0), vec![0u8; PAGE_SIZE]);
+        assert_eq!(read_page(&mut zram, 1), text_page(3));
+    }
+
+    #[test]
+    fn test_sector_writes_patch_the_page() {
+        let mut zram = Zram::new(MIB);
+        let text = text_page(4);
+        zram.write(0, &text).unwrap();
+        zram.write(1024, &[0xaa; SECTOR_SIZE * 2]).unwrap();
+
+        let mut expected = text.clone();
+        expected[1024..2048].fill(0xaa);
+        assert_eq!(read_page(&mut zram, 0), expected);
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        zram.read(1536, &mut sector).unwrap();
+        assert_eq!(sector, [0xaa; SECTOR_SIZE]);
+    }
+
+    #[test]
+    fn test_requests_are_checked() {
+        let mut zram = Zram::new(MIB);
+        let mut buffer = [0u8; SECTOR_SIZE];
+        assert_eq!(zram.read(100, &mut buffer), Err(ZramError::Unaligned));
+        assert_eq!(zram.write(0, &[0u8; 100]), Err(ZramError::Unaligned));
+        assert_eq!(zram.read(MIB, &mut buffer), Err(ZramError::OutOfRange));
+        assert_eq!(zram.discard(u64::MAX - 511, 512), Err(ZramError::OutOfRange));
+        assert_eq!(Zram::new(5000).disk_size(), 2 * PAGE_SIZE as u64);
+    }
+
+    #[test]
+    fn test_the_memory_limit_refuses_new_storage() {
+        let mut zram = Zram::new(MIB);
+        zram.set_mem_limit(PAGE_SIZE as u64);
+        zram.write(0, &noise_page(7)).unwrap();
+        assert_eq!(zram.write(PAGE_SIZE as u64, &noise_page(8)), Err(ZramError::MemoryLimit));
+        // Zero pages cost nothing and still fit
+        zram.write(PAGE_SIZE as u64, &[0u8; PAGE_SIZE]).unwrap();
+        // Replacing the stored page frees its storage first
+        zram.write(0, &noise_page(9)).unwrap();
+
+        let stats = zram.stats();
+        assert_eq!(stats.failed_writes, 1);
+        assert_eq!(stats.pages_stored, 2);
+        assert_eq!(read_page(&mut zram, 0), noise_page(9));
+    }
+
+    #[test]
+    fn test_ratio_and_savings() {
+        let mut zram = Zram::new(MIB);
+        assert_eq!(zram.stats().compression_ratio(), 0.0);
+        for index in 0..16 {
+            zram.write(index * PAGE_SIZE as u64, &text_page(index as u8)).unwrap();
+        }
+        for index in 16..32 {
+            zram.write(index * PAGE_SIZE as u64, &[0u8; PAGE_SIZE]).unwrap();
+        }
+
+        let stats = zram.stats();
+        assert_eq!(stats.orig_data_size, 32 * PAGE_SIZE as u64);
+        assert!(stats.compression_ratio() > 20.0);
+        assert_eq!(stats.memory_saved(), stats.orig_data_size - stats.mem_used);
+        assert!(stats.memory_saved() > 100 * 1024);
+    }
+}
@@ -0,0 +1,31 @@
+/*
+ * Orion Operating System - Compressed RAM Block Device
+ *
+ * A block device whose sectors live in memory, compressed a page at a
+ * time with LZ4, so that swap or a tmpfs backing store costs a fraction
+ * of the RAM it holds. Pages made of a single repeated word, zero pages
+ * above all, are recorded by their fill value and take no storage; pages
+ * that do not compress are kept as they are rather than paying for a
+ * blob larger than the page. The device reports what it holds against
+ * what that costs, which is the figure that decides whether the swap is
+ * worth having.
+ *
+ * The LZ4 codec is the standard block format, usable on its own by the
+ * compression driver.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod device;
+pub mod lz4;
+
+pub use device::{Zram, ZramError, ZramStats, PAGE_SIZE, SECTOR_SIZE};
+pub use lz4::{compress, compress_bound, decompress, Lz4Error};
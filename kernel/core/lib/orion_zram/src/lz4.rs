@@ -0,0 +1,290 @@
+/*
+ * Orion Operating System - LZ4 Block Codec
+ *
+ * The LZ4 block format: a run of sequences, each a token byte holding the
+ * literal and match lengths, the literals themselves, a two byte offset
+ * back into the output and the remainder of the match length. Lengths of
+ * 15 and more continue in extra bytes of 255. The last sequence is
+ * literals only, and the format requires it to cover at least the final
+ * five bytes, with no match starting in the last twelve.
+ *
+ * The compressor is the single pass greedy matcher of the reference
+ * implementation: a hash of the next four bytes finds the last position
+ * that started with the same hash, and a match is taken whenever that
+ * position is within the 64KB window and really holds the same bytes.
+ * The decompressor checks every length and offset against the input and
+ * the output limit, since it is fed data that may have been corrupted.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+const MIN_MATCH: usize = 4;
+/// Bytes at the end of the input that are always literals
+const LAST_LITERALS: usize = 5;
+/// No match may start within this many bytes of the end
+const MF_LIMIT: usize = 12;
+const MAX_DISTANCE: usize = 65535;
+const HASH_LOG: u32 = 12;
+/// Length nibble that continues in extra bytes
+const RUN_MASK: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lz4Error {
+    /// A sequence runs past the end of the input
+    Truncated,
+    /// A match points before the start of the output, or at offset 0
+    BadOffset,
+    /// The output would exceed the caller's limit
+    OutputTooLarge,
+}
+
+/// Largest compressed size of `length` input bytes
+pub fn compress_bound(length: usize) -> usize {
+    length + length / 255 + 16
+}
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+fn write_length(output: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        output.push(255);
+        length -= 255;
+    }
+    output.push(length as u8);
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], offset: usize, match_length: usize) {
+    let match_extra = match_length - MIN_MATCH;
+    let token = (literals.len().min(RUN_MASK) << 4) | match_extra.min(RUN_MASK);
+    output.push(token as u8);
+    if literals.len() >= RUN_MASK {
+        write_length(output, literals.len() - RUN_MASK);
+    }
+    output.extend_from_slice(literals);
+    output.extend_from_slice(&(offset as u16).to_le_bytes());
+    if match_extra >= RUN_MASK {
+        write_length(output, match_extra - RUN_MASK);
+    }
+}
+
+fn write_last_literals(output: &mut Vec<u8>, literals: &[u8]) {
+    output.push((literals.len().min(RUN_MASK) << 4) as u8);
+    if literals.len() >= RUN_MASK {
+        write_length(output, literals.len() - RUN_MASK);
+    }
+    output.extend_from_slice(literals);
+}
+
+/// Compress `input` into one LZ4 block
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(compress_bound(input.len()));
+    let mut anchor = 0;
+
+    if input.len() > MF_LIMIT {
+        // Positions are stored plus one so that 0 means empty
+        let mut table = vec![0u32; 1 << HASH_LOG];
+        let match_limit = input.len() - MF_LIMIT;
+        let extend_limit = input.len() - LAST_LITERALS;
+        let mut position = 0;
+
+        while position < match_limit {
+            let sequence = read_u32(input, position);
+            let slot = hash(sequence);
+            let candidate = table[slot] as usize;
+            table[slot] = position as u32 + 1;
+
+            if candidate == 0 {
+                position += 1;
+                continue;
+            }
+            let candidate = candidate - 1;
+            if position - candidate > MAX_DISTANCE || read_u32(input, candidate) != sequence {
+                position += 1;
+                continue;
+            }
+
+            let mut length = MIN_MATCH;
+            while position + length < extend_limit && input[candidate + length] == input[position + length] {
+                length += 1;
+            }
+            // Take back literals that belong to the match
+            let (mut start, mut source) = (position, candidate);
+            while start > anchor && source > 0 && input[start - 1] == input[source - 1] {
+                start -= 1;
+                source -= 1;
+                length += 1;
+            }
+
+            write_sequence(&mut output, &input[anchor..start], start - source, length);
+            position = start + length;
+            anchor = position;
+        }
+    }
+
+    write_last_literals(&mut output, &input[anchor..]);
+    output
+}
+
+fn read_length(input: &[u8], position: &mut usize) -> Result<usize, Lz4Error> {
+    let mut length = 0usize;
+    loop {
+        let byte = *input.get(*position).ok_or(Lz4Error::Truncated)?;
+        *position += 1;
+        length = length.checked_add(byte as usize).ok_or(Lz4Error::OutputTooLarge)?;
+        if byte != 255 {
+            return Ok(length);
+        }
+    }
+}
+
+/// Decompress one LZ4 block, producing at most `max_output` bytes
+pub fn decompress(input: &[u8], max_output: usize) -> Result<Vec<u8>, Lz4Error> {
+    let mut output = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let token = *input.get(position).ok_or(Lz4Error::Truncated)? as usize;
+        position += 1;
+
+        let mut literals = token >> 4;
+        if literals == RUN_MASK {
+            literals += read_length(input, &mut position)?;
+        }
+        let end = position.checked_add(literals).ok_or(Lz4Error::Truncated)?;
+        if end > input.len() {
+            return Err(Lz4Error::Truncated);
+        }
+        if output.len() + literals > max_output {
+            return Err(Lz4Error::OutputTooLarge);
+        }
+        output.extend_from_slice(&input[position..end]);
+        position = end;
+
+        // The last sequence stops after its literals
+        if position == input.len() {
+            return Ok(output);
+        }
+
+        if position + 2 > input.len() {
+            return Err(Lz4Error::Truncated);
+        }
+        let offset = u16::from_le_bytes([input[position], input[position + 1]]) as usize;
+        position += 2;
+        if offset == 0 || offset > output.len() {
+            return Err(Lz4Error::BadOffset);
+        }
+
+        let mut length = token & RUN_MASK;
+        if length == RUN_MASK {
+            length += read_length(input, &mut position)?;
+        }
+        length += MIN_MATCH;
+        if output.len() + length > max_output {
+            return Err(Lz4Error::OutputTooLarge);
+        }
+
+        // Byte by byte: the match may overlap the bytes it produces
+        let start = output.len() - offset;
+        for index in 0..length {
+            let byte = output[start + index];
+            output.push(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let compressed = compress(data);
+        assert!(compressed.len() <= compress_bound(data.len()));
+        let restored = decompress(&compressed, data.len()).unwrap();
+        assert_eq!(restored, data);
+        compressed
+    }
+
+    /// Deterministic bytes that do not compress
+    fn noise(length: usize) -> Vec<u8> {
+        let mut state = 0x2545f491u32;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_and_short_inputs_are_literals() {
+        assert_eq!(round_trip(&[]), [0x00]);
+        assert_eq!(round_trip(b"abcabcabcab"), b"\xb0abcabcabcab");
+    }
+
+    #[test]
+    fn test_repeated_text_compresses() {
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(90);
+        let compressed = round_trip(&text);
+        assert!(compressed.len() < text.len() / 10);
+    }
+
+    #[test]
+    fn test_long_runs_use_extended_lengths() {
+        let mut data = vec![7u8; 5000];
+        data.extend_from_slice(&noise(300));
+        let compressed = round_trip(&data);
+        assert!(compressed.len() < 400);
+    }
+
+    #[test]
+    fn test_the_format_rules_at_the_end_hold() {
+        let data = vec![0u8; 64];
+        let compressed = round_trip(&data);
+        // One match followed by five trailing literals
+        let tail = &compressed[compressed.len() - 6..];
+        assert_eq!(tail, [0x50, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_incompressible_data_stays_within_the_bound() {
+        let data = noise(4096);
+        let compressed = round_trip(&data);
+        assert!(compressed.len() > data.len());
+    }
+
+    #[test]
+    fn test_corrupted_blocks_are_rejected() {
+        let data = b"abcdabcdabcdabcdabcdabcdabcdabcd".repeat(4);
+        let compressed = compress(&data);
+
+        assert_eq!(decompress(&compressed[..compressed.len() - 3], 4096), Err(Lz4Error::Truncated));
+        assert_eq!(decompress(&compressed, 16), Err(Lz4Error::OutputTooLarge));
+
+        // A match reaching back before the first byte
+        assert_eq!(decompress(&[0x10, b'a', 0x05, 0x00, 0x00], 64), Err(Lz4Error::BadOffset));
+        assert_eq!(decompress(&[0x10, b'a', 0x00, 0x00, 0x00], 64), Err(Lz4Error::BadOffset));
+        assert_eq!(decompress(&[], 64), Err(Lz4Error::Truncated));
+    }
+
+    #[test]
+    fn test_overlapping_matches_repeat_the_pattern() {
+        // "ab" then a 10 byte match at offset 2, then five literals
+        let block = [0x26, b'a', b'b', 0x02, 0x00, 0x50, b'x', b'y', b'z', b'x', b'y'];
+        assert_eq!(decompress(&block, 64).unwrap(), b"ababababababxyzxy");
+    }
+}
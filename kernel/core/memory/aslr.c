@@ -0,0 +1,307 @@
+/*
+ * Orion Operating System - Address Space Randomization and Guard Pages
+ *
+ * Random placement of process regions and the table of guard pages
+ * protecting stacks and DMA buffers. Guards are never mapped: the
+ * allocators of vmm.c skip them, so a guard stays a hole for the lifetime
+ * of the mapping it protects and any access to it faults.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/aslr.h>
+#include <orion/vma.h>
+
+extern uint64_t security_get_random(void);
+
+static guard_region_t g_guards[ASLR_MAX_GUARDS];
+static spinlock_t g_guards_lock = SPINLOCK_INIT;
+static bool g_aslr_enabled = true;
+
+void aslr_init(void)
+{
+    memset(g_guards, 0, sizeof(g_guards));
+    kinfo("ASLR: randomization %s, %u stack guard pages, %u DMA guard pages",
+          g_aslr_enabled ? "enabled" : "disabled", ASLR_STACK_GUARD_PAGES, ASLR_DMA_GUARD_PAGES);
+}
+
+bool aslr_enabled(void)
+{
+    return g_aslr_enabled;
+}
+
+void aslr_set_enabled(bool enabled)
+{
+    g_aslr_enabled = enabled;
+    kinfo("ASLR: randomization %s", enabled ? "enabled" : "disabled");
+}
+
+uint64_t aslr_random_base(uint64_t lo, uint64_t hi, uint64_t size)
+{
+    lo = ROUND_UP(lo, PAGE_SIZE);
+    size = ROUND_UP(size, PAGE_SIZE);
+    if (!g_aslr_enabled || hi <= lo || hi - lo <= size)
+    {
+        return lo;
+    }
+    uint64_t slots = (hi - lo - size) / PAGE_SIZE + 1;
+    return lo + (security_get_random() % slots) * PAGE_SIZE;
+}
+
+// ========================================
+// GUARD TABLE
+// ========================================
+
+static bool guard_overlaps(vm_space_t *space, uint64_t start, uint64_t end)
+{
+    for (int i = 0; i < ASLR_MAX_GUARDS; i++)
+    {
+        const guard_region_t *guard = &g_guards[i];
+        if (guard->kind && guard->space == space && guard->start < end && start < guard->end)
+        {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Record a guard; the caller holds g_guards_lock
+static int guard_add(const guard_region_t *region)
+{
+    for (int i = 0; i < ASLR_MAX_GUARDS; i++)
+    {
+        if (!g_guards[i].kind)
+        {
+            g_guards[i] = *region;
+            return OR_OK;
+        }
+    }
+    return -OR_ENOSPC;
+}
+
+// Drop the guards protecting the mapping starting at owner_start
+static void guard_remove_owner(vm_space_t *space, uint64_t owner_start)
+{
+    spinlock_lock(&g_guards_lock);
+    for (int i = 0; i < ASLR_MAX_GUARDS; i++)
+    {
+        if (g_guards[i].kind && g_guards[i].space == space && g_guards[i].owner_start == owner_start)
+        {
+            g_guards[i].kind = 0;
+        }
+    }
+    spinlock_unlock(&g_guards_lock);
+}
+
+bool aslr_guard_lookup(vm_space_t *space, uint64_t vaddr, guard_region_t *out)
+{
+    bool found = false;
+    spinlock_lock(&g_guards_lock);
+    for (int i = 0; i < ASLR_MAX_GUARDS; i++)
+    {
+        const guard_region_t *guard = &g_guards[i];
+        if (guard->kind && guard->space == space && vaddr >= guard->start && vaddr < guard->end)
+        {
+            if (out)
+            {
+                *out = *guard;
+            }
+            found = true;
+            break;
+        }
+    }
+    spinlock_unlock(&g_guards_lock);
+    return found;
+}
+
+bool aslr_is_guard(vm_space_t *space, uint64_t vaddr)
+{
+    return aslr_guard_lookup(space, vaddr, NULL);
+}
+
+bool aslr_range_has_guard(vm_space_t *space, uint64_t start, uint64_t end)
+{
+    spinlock_lock(&g_guards_lock);
+    bool overlaps = guard_overlaps(space, start, end);
+    spinlock_unlock(&g_guards_lock);
+    return overlaps;
+}
+
+int aslr_clone_space(vm_space_t *parent, vm_space_t *child, uint64_t pid)
+{
+    int result = OR_OK;
+    spinlock_lock(&g_guards_lock);
+    for (int i = 0; i < ASLR_MAX_GUARDS && result == OR_OK; i++)
+    {
+        guard_region_t guard = g_guards[i];
+        if (guard.kind && guard.space == parent && guard.kind != GUARD_DMA_UNDERRUN &&
+            guard.kind != GUARD_DMA_OVERRUN)
+        {
+            guard.space = child;
+            guard.pid = pid;
+            result = guard_add(&guard);
+        }
+    }
+    spinlock_unlock(&g_guards_lock);
+    return result;
+}
+
+void aslr_release_space(vm_space_t *space)
+{
+    spinlock_lock(&g_guards_lock);
+    for (int i = 0; i < ASLR_MAX_GUARDS; i++)
+    {
+        if (g_guards[i].space == space)
+        {
+            g_guards[i].kind = 0;
+        }
+    }
+    spinlock_unlock(&g_guards_lock);
+}
+
+const char *aslr_guard_kind_name(guard_kind_t kind)
+{
+    switch (kind)
+    {
+    case GUARD_STACK_OVERFLOW:
+        return "stack overflow";
+    case GUARD_STACK_UNDERFLOW:
+        return "stack underflow";
+    case GUARD_DMA_UNDERRUN:
+        return "DMA buffer underrun";
+    case GUARD_DMA_OVERRUN:
+        return "DMA buffer overrun";
+    }
+    return "unknown";
+}
+
+// ========================================
+// GUARDED ALLOCATIONS
+// ========================================
+
+// Pages and guards only; the area tree is checked before g_guards_lock is
+// taken, since area placement looks guards up with the tree locked
+static bool range_is_free(vm_space_t *space, uint64_t start, size_t pages)
+{
+    for (size_t i = 0; i < pages; i++)
+    {
+        if (mmu_is_valid_addr(start + i * PAGE_SIZE))
+        {
+            return false;
+        }
+    }
+    return !guard_overlaps(space, start, start + pages * PAGE_SIZE);
+}
+
+// Place a guarded mapping whose first page is `align` aligned; `dma`
+// backs it with contiguous DMA32 frames
+static uint64_t alloc_guarded(vm_space_t *space, uint64_t pid, uint64_t lo, uint64_t hi, size_t count,
+                              size_t guard_pages, uint64_t flags, guard_kind_t below, guard_kind_t above, bool dma,
+                              uint64_t align)
+{
+    if (!space || count == 0)
+    {
+        return 0;
+    }
+
+    size_t total = count + 2 * guard_pages;
+    uint64_t guard_size = guard_pages * PAGE_SIZE;
+
+    for (int attempt = 0; attempt < ASLR_PLACEMENT_TRIES; attempt++)
+    {
+        uint64_t start = aslr_random_base(lo, hi, total * PAGE_SIZE + align - PAGE_SIZE);
+        start = ROUND_UP(start + guard_size, align) - guard_size;
+        bool usable = start + total * PAGE_SIZE <= hi && !vma_overlaps(space, start, start + total * PAGE_SIZE);
+        spinlock_lock(&g_guards_lock);
+        if (!usable || !range_is_free(space, start, total))
+        {
+            spinlock_unlock(&g_guards_lock);
+            if (!g_aslr_enabled)
+            {
+                break; // Every attempt would pick the same base
+            }
+            continue;
+        }
+
+        uint64_t vaddr = start + guard_size;
+        uint64_t end = vaddr + count * PAGE_SIZE;
+        guard_region_t low = {space, pid, start, vaddr, vaddr, end, below};
+        guard_region_t high = {space, pid, end, end + guard_size, vaddr, end, above};
+        if (guard_pages && (guard_add(&low) != OR_OK || guard_add(&high) != OR_OK))
+        {
+            spinlock_unlock(&g_guards_lock);
+            guard_remove_owner(space, vaddr);
+            kerror("aslr_alloc_guarded: guard table full");
+            return 0;
+        }
+        spinlock_unlock(&g_guards_lock);
+
+        int result = dma ? vmm_alloc_dma_at(space, vaddr, count, flags)
+                         : vmm_alloc_pages_at(space, vaddr, count, flags);
+        if (result != OR_OK)
+        {
+            guard_remove_owner(space, vaddr);
+            return 0;
+        }
+
+        vma_t area = {0};
+        area.start = vaddr;
+        area.end = end;
+        area.prot = flags;
+        area.type = VMA_ANONYMOUS;
+        area.flags = dma ? (VMA_LOCKED | VMA_DMA) : 0;
+        if (vma_insert(space, &area) != OR_OK)
+        {
+            // Lost the range to a concurrent mapping
+            vmm_free_pages(space, vaddr, count);
+            guard_remove_owner(space, vaddr);
+            continue;
+        }
+        kdebug("aslr_alloc_guarded: %llu pages at 0x%p, %llu guard pages each side (pid %llu)",
+               (unsigned long long)count, (void *)vaddr, (unsigned long long)guard_pages,
+               (unsigned long long)pid);
+        return vaddr;
+    }
+
+    kerror("aslr_alloc_guarded: no free range of %llu pages in 0x%p - 0x%p",
+           (unsigned long long)total, (void *)lo, (void *)hi);
+    return 0;
+}
+
+uint64_t aslr_alloc_guarded(vm_space_t *space, uint64_t pid, uint64_t lo, uint64_t hi, size_t count,
+                            size_t guard_pages, uint64_t flags, guard_kind_t below, guard_kind_t above)
+{
+    return alloc_guarded(space, pid, lo, hi, count, guard_pages, flags, below, above, false, PAGE_SIZE);
+}
+
+void aslr_free_guarded(vm_space_t *space, uint64_t vaddr, size_t count)
+{
+    vma_unmap(space, vaddr, count * PAGE_SIZE);
+    guard_remove_owner(space, vaddr);
+}
+
+uint64_t aslr_alloc_stack(vm_space_t *space, uint64_t pid, size_t count)
+{
+    return aslr_alloc_guarded(space, pid, ASLR_STACK_TOP - ASLR_STACK_RANGE, ASLR_STACK_TOP, count,
+                              ASLR_STACK_GUARD_PAGES, VM_FLAG_READ | VM_FLAG_WRITE | VM_FLAG_USER,
+                              GUARD_STACK_OVERFLOW, GUARD_STACK_UNDERFLOW);
+}
+
+uint64_t aslr_alloc_dma(vm_space_t *space, uint64_t pid, size_t count, uint64_t flags)
+{
+    return alloc_guarded(space, pid, ASLR_DMA_BASE, ASLR_DMA_BASE + ASLR_DMA_RANGE, count, ASLR_DMA_GUARD_PAGES,
+                         flags, GUARD_DMA_UNDERRUN, GUARD_DMA_OVERRUN, true, PAGE_SIZE);
+}
+
+uint64_t aslr_alloc_dma_huge(vm_space_t *space, uint64_t pid, size_t count, uint64_t flags)
+{
+    return alloc_guarded(space, pid, ASLR_DMA_BASE, ASLR_DMA_BASE + ASLR_DMA_RANGE, count, ASLR_DMA_GUARD_PAGES,
+                         flags, GUARD_DMA_UNDERRUN, GUARD_DMA_OVERRUN, true, HUGE_PAGE_SIZE);
+}
@@ -0,0 +1,107 @@
+/*
+ * Orion Operating System - Address Space Randomization and Guard Pages
+ *
+ * Servers and drivers are loaded at random addresses: the code and data
+ * segments, the heap and the stack of every process are placed at a
+ * page-aligned offset drawn from the kernel entropy pool. Stacks and DMA
+ * buffers are surrounded by unmapped guard pages, recorded per address
+ * space, so that an overflow faults on the first access past the end
+ * instead of corrupting the neighbouring mapping. The page fault handler
+ * looks faulting addresses up here and reports hits to the crash-dump
+ * subsystem as "guard page hit" faults.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_ASLR_H
+#define ORION_ASLR_H
+
+#include <orion/types.h>
+#include <orion/mm.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ASLR_MAX_GUARDS 1024
+
+// Unmapped pages on each side of a stack and of a DMA buffer
+#define ASLR_STACK_GUARD_PAGES 4
+#define ASLR_DMA_GUARD_PAGES 1
+
+// Windows the randomized regions are placed in
+#define ASLR_CODE_BASE 0x0000000000400000ULL
+#define ASLR_CODE_RANGE (1ULL << 30)  // 1 GiB, 18 bits of entropy
+#define ASLR_HEAP_GAP (1ULL << 32)    // Heap starts within 4 GiB above the data
+#define ASLR_STACK_TOP 0x00007FFFFFFFF000ULL
+#define ASLR_STACK_RANGE (1ULL << 34) // 16 GiB below the top of user space
+#define ASLR_DMA_BASE 0x0000100000000000ULL
+#define ASLR_DMA_RANGE (1ULL << 36)
+
+// Random placements tried before giving up on a region
+#define ASLR_PLACEMENT_TRIES 16
+
+    typedef enum guard_kind
+    {
+        GUARD_STACK_OVERFLOW = 1, // Below a stack: it grew past its size
+        GUARD_STACK_UNDERFLOW,    // Above a stack: popped past its top
+        GUARD_DMA_UNDERRUN,       // Before a DMA buffer
+        GUARD_DMA_OVERRUN,        // After a DMA buffer
+    } guard_kind_t;
+
+    // Unmapped range protecting the mapping [owner_start, owner_end)
+    typedef struct guard_region
+    {
+        vm_space_t *space;
+        uint64_t pid;
+        uint64_t start;
+        uint64_t end;
+        uint64_t owner_start;
+        uint64_t owner_end;
+        guard_kind_t kind;
+    } guard_region_t;
+
+    void aslr_init(void);
+    bool aslr_enabled(void);
+    void aslr_set_enabled(bool enabled);
+
+    // Page-aligned random base for `size` bytes within [lo, hi). Returns lo
+    // when randomization is disabled
+    uint64_t aslr_random_base(uint64_t lo, uint64_t hi, uint64_t size);
+
+    // Map `count` pages at a random base within [lo, hi), leaving
+    // `guard_pages` unmapped pages on each side recorded as guards of the
+    // given kinds. Returns the first mapped page, or 0
+    uint64_t aslr_alloc_guarded(vm_space_t *space, uint64_t pid, uint64_t lo, uint64_t hi, size_t count,
+                                size_t guard_pages, uint64_t flags, guard_kind_t below, guard_kind_t above);
+    // Unmap a mapping made by aslr_alloc_guarded and drop its guards
+    void aslr_free_guarded(vm_space_t *space, uint64_t vaddr, size_t count);
+
+    // Stacks and DMA buffers, the latter physically contiguous below 4GB
+    uint64_t aslr_alloc_stack(vm_space_t *space, uint64_t pid, size_t count);
+    uint64_t aslr_alloc_dma(vm_space_t *space, uint64_t pid, size_t count, uint64_t flags);
+    // DMA buffer starting on a 2MB boundary, so that it is mapped with
+    // huge pages (see vmm_alloc_dma_at)
+    uint64_t aslr_alloc_dma_huge(vm_space_t *space, uint64_t pid, size_t count, uint64_t flags);
+
+    // Guard regions
+    bool aslr_guard_lookup(vm_space_t *space, uint64_t vaddr, guard_region_t *out);
+    bool aslr_is_guard(vm_space_t *space, uint64_t vaddr);
+    // Whether a guard of `space` overlaps [start, end)
+    bool aslr_range_has_guard(vm_space_t *space, uint64_t start, uint64_t end);
+    // Give a forked space the guards of its parent's stacks; DMA buffers
+    // and their guards are not inherited
+    int aslr_clone_space(vm_space_t *parent, vm_space_t *child, uint64_t pid);
+    void aslr_release_space(vm_space_t *space);
+    const char *aslr_guard_kind_name(guard_kind_t kind);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_ASLR_H
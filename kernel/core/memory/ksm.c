@@ -0,0 +1,547 @@
+/*
+ * Orion Operating System - Same-Page Merging
+ *
+ * The scanner walks the mergeable anonymous areas of the registered
+ * spaces a batch at a time, resuming where the previous batch stopped.
+ * Each page it looks at is checksummed; a page only becomes a candidate
+ * once its checksum has stayed the same for the minimum age, which keeps
+ * pages still being written out of the way. A candidate is then merged
+ * into a merged frame with the same contents if there is one, or with a
+ * twin seen earlier in the same pass, which becomes the merged frame.
+ *
+ * Merging write-protects the page first and compares it byte for byte
+ * with the merged frame afterwards, so a write racing with the scanner
+ * either lands before the comparison (and the page is left alone) or
+ * faults on the protected page and is given write access back. Merged
+ * frames are mapped copy-on-write and carry one extra share held by the
+ * scanner, so a listed frame is never freed under it; at the end of each
+ * pass, frames that at most one mapping still uses are handed back to it.
+ *
+ * A batch runs under the KSM lock, which also keeps the spaces it walks
+ * from being destroyed under it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/vma.h>
+#include "ksm.h"
+
+// Pages whose age is tracked and candidates of a pass, both direct
+// mapped: a page whose slot is taken over by another starts again
+#define KSM_TRACKED_SLOTS 8192
+#define KSM_CANDIDATE_SLOTS 4096
+
+// Merged frames are looked up among this many slots from their checksum
+#define KSM_STABLE_PROBES 4
+
+// A page seen by the scanner
+typedef struct ksm_item
+{
+    vm_space_t *space; // NULL for a free slot
+    uint64_t vaddr;
+    uint64_t checksum;
+    uint64_t since_ns; // Contents unchanged since
+} ksm_item_t;
+
+// A page old enough to be merged, waiting for a twin
+typedef struct ksm_candidate
+{
+    vm_space_t *space;
+    uint64_t vaddr;
+    uint64_t checksum;
+    uint64_t pass; // Only valid during the pass that recorded it
+} ksm_candidate_t;
+
+typedef struct ksm_frame
+{
+    uint64_t paddr; // 0 for a free slot
+    uint64_t checksum;
+} ksm_frame_t;
+
+static vm_space_t *g_spaces[KSM_MAX_SPACES];
+static ksm_item_t g_items[KSM_TRACKED_SLOTS];
+static ksm_candidate_t g_candidates[KSM_CANDIDATE_SLOTS];
+static ksm_frame_t g_stable[KSM_MAX_STABLE];
+static ksm_config_t g_config;
+static ksm_stats_t g_stats;
+static spinlock_t g_ksm_lock = SPINLOCK_INIT;
+
+// Scan position
+static uint32_t g_cursor_space;
+static uint64_t g_cursor_addr;
+static uint64_t g_pass = 1;
+static uint64_t g_next_batch_ns;
+static bool g_scanning; // A CPU is running a batch
+
+// ========================================
+// PAGES
+// ========================================
+
+// Frames are reached through the direct mapping, as the COW handler does
+static uint64_t page_checksum(uint64_t paddr)
+{
+    const uint64_t *words = (const uint64_t *)paddr;
+    uint64_t hash = 0xcbf29ce484222325ULL;
+    for (size_t i = 0; i < PAGE_SIZE / sizeof(uint64_t); i++)
+    {
+        hash = (hash ^ words[i]) * 0x100000001b3ULL;
+    }
+    return hash;
+}
+
+static ksm_item_t *item_slot(vm_space_t *space, uint64_t vaddr)
+{
+    uint64_t key = ((uint64_t)space ^ (vaddr >> 12)) * 0x9e3779b97f4a7c15ULL;
+    return &g_items[(key >> 32) % KSM_TRACKED_SLOTS];
+}
+
+// Caller holds g_ksm_lock
+static ksm_frame_t *stable_find(uint64_t checksum)
+{
+    for (uint32_t i = 0; i < KSM_STABLE_PROBES; i++)
+    {
+        ksm_frame_t *frame = &g_stable[(checksum + i) % KSM_MAX_STABLE];
+        if (frame->paddr && frame->checksum == checksum)
+        {
+            return frame;
+        }
+    }
+    return NULL;
+}
+
+// Caller holds g_ksm_lock
+static ksm_frame_t *stable_slot(uint64_t checksum)
+{
+    for (uint32_t i = 0; i < KSM_STABLE_PROBES; i++)
+    {
+        ksm_frame_t *frame = &g_stable[(checksum + i) % KSM_MAX_STABLE];
+        if (!frame->paddr)
+        {
+            return frame;
+        }
+    }
+    return NULL;
+}
+
+// Caller holds g_ksm_lock
+static bool stable_contains(uint64_t paddr)
+{
+    for (uint32_t i = 0; i < KSM_MAX_STABLE; i++)
+    {
+        if (g_stable[i].paddr == paddr)
+        {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Replace the page `paddr` mapped at `vaddr` with `flags` by the merged
+// frame `frame`. Caller holds g_ksm_lock
+static bool merge_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t flags, uint64_t frame)
+{
+    if (pmm_page_share(frame) != OR_OK)
+    {
+        return false;
+    }
+
+    // No write can change the page between the comparison and the switch
+    uint64_t shared = (flags & ~PAGE_FLAG_WRITE) | PAGE_FLAG_COW;
+    if (vmm_protect_page(space, vaddr, shared) != OR_OK)
+    {
+        pmm_free_page(frame);
+        return false;
+    }
+
+    uint64_t now_paddr = 0;
+    uint64_t now_flags = 0;
+    bool mapped = vmm_query_page(space, vaddr, &now_paddr, &now_flags) && now_paddr == paddr;
+    if (!mapped || (now_flags & PAGE_FLAG_WRITE) || memcmp((void *)paddr, (void *)frame, PAGE_SIZE) != 0)
+    {
+        // Written meanwhile, or not the same contents after all
+        if (mapped)
+        {
+            vmm_protect_page(space, vaddr, flags);
+        }
+        pmm_free_page(frame);
+        return false;
+    }
+
+    if (vmm_map_page(space, vaddr, frame, shared) != OR_OK)
+    {
+        vmm_protect_page(space, vaddr, flags);
+        pmm_free_page(frame);
+        return false;
+    }
+    pmm_free_page(paddr);
+    return true;
+}
+
+// Turn the page at `vaddr` of `space` into a merged frame, if it still
+// holds the contents summed to `checksum`. Caller holds g_ksm_lock
+static ksm_frame_t *promote(vm_space_t *space, uint64_t vaddr, uint64_t checksum)
+{
+    ksm_frame_t *slot = stable_slot(checksum);
+    uint64_t paddr;
+    uint64_t flags;
+    if (!slot || !vmm_query_page(space, vaddr, &paddr, &flags) || pmm_page_mapcount(paddr) != 1)
+    {
+        return NULL;
+    }
+    if (pmm_page_share(paddr) != OR_OK)
+    {
+        return NULL;
+    }
+    if (vmm_protect_page(space, vaddr, (flags & ~PAGE_FLAG_WRITE) | PAGE_FLAG_COW) != OR_OK)
+    {
+        pmm_free_page(paddr);
+        return NULL;
+    }
+    // Summed again now that it can no longer change
+    if (page_checksum(paddr) != checksum)
+    {
+        vmm_protect_page(space, vaddr, flags);
+        pmm_free_page(paddr);
+        return NULL;
+    }
+
+    slot->paddr = paddr;
+    slot->checksum = checksum;
+    return slot;
+}
+
+// Caller holds g_ksm_lock
+static void scan_page(vm_space_t *space, uint64_t vaddr, uint64_t now)
+{
+    g_stats.pages_scanned++;
+
+    // Not populated, pinned, or already shared: merged, or forked and
+    // left to copy-on-write
+    uint64_t paddr;
+    uint64_t flags;
+    if (!vmm_query_page(space, vaddr, &paddr, &flags) || (flags & PAGE_FLAG_LOCKED) ||
+        pmm_page_mapcount(paddr) != 1)
+    {
+        return;
+    }
+
+    uint64_t checksum = page_checksum(paddr);
+    ksm_item_t *item = item_slot(space, vaddr);
+    bool tracked = item->space == space && item->vaddr == vaddr;
+    if (!tracked || item->checksum != checksum)
+    {
+        if (tracked)
+        {
+            g_stats.volatile_pages++;
+        }
+        item->space = space;
+        item->vaddr = vaddr;
+        item->checksum = checksum;
+        item->since_ns = now;
+        return;
+    }
+    if (now - item->since_ns < g_config.min_age_ns)
+    {
+        return;
+    }
+
+    ksm_frame_t *frame = stable_find(checksum);
+    if (frame)
+    {
+        if (merge_page(space, vaddr, paddr, flags, frame->paddr))
+        {
+            g_stats.merges++;
+        }
+        return;
+    }
+
+    // A twin from earlier in this pass becomes the merged frame
+    ksm_candidate_t *twin = &g_candidates[checksum % KSM_CANDIDATE_SLOTS];
+    if (twin->pass == g_pass && twin->checksum == checksum && (twin->space != space || twin->vaddr != vaddr))
+    {
+        twin->pass = 0;
+        frame = promote(twin->space, twin->vaddr, checksum);
+        if (frame && merge_page(space, vaddr, paddr, flags, frame->paddr))
+        {
+            g_stats.merges++;
+            return;
+        }
+    }
+    twin->space = space;
+    twin->vaddr = vaddr;
+    twin->checksum = checksum;
+    twin->pass = g_pass;
+}
+
+// ========================================
+// SCANNER (g_ksm_lock held)
+// ========================================
+
+static int space_slot(vm_space_t *space)
+{
+    for (int i = 0; i < KSM_MAX_SPACES; i++)
+    {
+        if (g_spaces[i] == space)
+        {
+            return i;
+        }
+    }
+    return -1;
+}
+
+static int register_space(vm_space_t *space)
+{
+    if (space_slot(space) >= 0)
+    {
+        return OR_OK;
+    }
+    int slot = space_slot(NULL);
+    if (slot < 0)
+    {
+        return -OR_ENOSPC;
+    }
+    g_spaces[slot] = space;
+    g_stats.spaces++;
+    return OR_OK;
+}
+
+// Hand back merged frames that at most one mapping still uses
+static void end_pass(void)
+{
+    g_pass++;
+    g_stats.full_scans++;
+    for (uint32_t i = 0; i < KSM_MAX_STABLE; i++)
+    {
+        if (g_stable[i].paddr && pmm_page_mapcount(g_stable[i].paddr) <= 2)
+        {
+            pmm_free_page(g_stable[i].paddr);
+            g_stable[i].paddr = 0;
+            g_stats.released++;
+        }
+    }
+}
+
+// First mergeable anonymous area of `space` ending above `addr`
+static bool next_area(vm_space_t *space, uint64_t addr, vma_t *area)
+{
+    while (vma_next(space, addr, area))
+    {
+        if ((area->flags & VMA_MERGEABLE) && area->type == VMA_ANONYMOUS && !(area->flags & VMA_LOCKED))
+        {
+            return true;
+        }
+        addr = area->end;
+    }
+    return false;
+}
+
+static void scan_batch(uint64_t now)
+{
+    uint32_t budget = g_config.pages_to_scan;
+    // Every space is looked at once at most, so a batch wraps once at most
+    for (uint32_t visits = 0; budget > 0 && visits <= KSM_MAX_SPACES;)
+    {
+        vm_space_t *space = g_spaces[g_cursor_space];
+        vma_t area;
+        if (!space || !next_area(space, g_cursor_addr, &area))
+        {
+            g_cursor_addr = 0;
+            if (++g_cursor_space == KSM_MAX_SPACES)
+            {
+                g_cursor_space = 0;
+                end_pass();
+            }
+            visits++;
+            continue;
+        }
+
+        uint64_t page = MAX(area.start, g_cursor_addr);
+        for (; page < area.end && budget > 0; page += PAGE_SIZE, budget--)
+        {
+            scan_page(space, page, now);
+        }
+        g_cursor_addr = page;
+    }
+}
+
+// ========================================
+// PUBLIC INTERFACE
+// ========================================
+
+void ksm_init(void)
+{
+    spinlock_init(&g_ksm_lock);
+    memset(g_spaces, 0, sizeof(g_spaces));
+    memset(g_items, 0, sizeof(g_items));
+    memset(g_candidates, 0, sizeof(g_candidates));
+    memset(g_stable, 0, sizeof(g_stable));
+    memset(&g_stats, 0, sizeof(g_stats));
+    g_config.enabled = 0;
+    g_config.pages_to_scan = KSM_DEFAULT_PAGES_TO_SCAN;
+    g_config.scan_interval_ns = KSM_DEFAULT_INTERVAL_NS;
+    g_config.min_age_ns = KSM_DEFAULT_MIN_AGE_NS;
+    kinfo("KSM: same-page merging ready, scanner off");
+}
+
+int ksm_advise(vm_space_t *space, uint64_t start, uint64_t length, bool mergeable)
+{
+    if (!space)
+    {
+        return -OR_EINVAL;
+    }
+    int result = vma_set_flags(space, start, length, mergeable ? VMA_MERGEABLE : 0, mergeable ? 0 : VMA_MERGEABLE);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    spinlock_lock(&g_ksm_lock);
+    if (mergeable)
+    {
+        result = register_space(space);
+        spinlock_unlock(&g_ksm_lock);
+        if (result != OR_OK)
+        {
+            vma_set_flags(space, start, length, 0, VMA_MERGEABLE);
+        }
+        return result;
+    }
+
+    // Writable merged pages get a private copy back; read-only ones can
+    // never differ from the merged frame and stay as they are
+    uint64_t end = start + ROUND_UP(length, PAGE_SIZE);
+    for (uint64_t page = start; page < end; page += PAGE_SIZE)
+    {
+        uint64_t paddr;
+        uint64_t flags;
+        if (vmm_query_page(space, page, &paddr, &flags) && (flags & PAGE_FLAG_COW) && stable_contains(paddr))
+        {
+            vmm_handle_cow_fault(space, page);
+        }
+    }
+    spinlock_unlock(&g_ksm_lock);
+    return OR_OK;
+}
+
+void ksm_fork_space(vm_space_t *parent, vm_space_t *child)
+{
+    spinlock_lock(&g_ksm_lock);
+    if (space_slot(parent) >= 0 && register_space(child) != OR_OK)
+    {
+        kwarning("KSM: no room for forked space 0x%p, its pages will not be merged", (void *)child);
+    }
+    spinlock_unlock(&g_ksm_lock);
+}
+
+void ksm_release_space(vm_space_t *space)
+{
+    spinlock_lock(&g_ksm_lock);
+    int slot = space_slot(space);
+    if (slot < 0)
+    {
+        spinlock_unlock(&g_ksm_lock);
+        return;
+    }
+    g_spaces[slot] = NULL;
+    g_stats.spaces--;
+    if (g_cursor_space == (uint32_t)slot)
+    {
+        g_cursor_addr = 0;
+    }
+    for (uint32_t i = 0; i < KSM_TRACKED_SLOTS; i++)
+    {
+        if (g_items[i].space == space)
+        {
+            g_items[i].space = NULL;
+        }
+    }
+    for (uint32_t i = 0; i < KSM_CANDIDATE_SLOTS; i++)
+    {
+        if (g_candidates[i].space == space)
+        {
+            g_candidates[i].pass = 0;
+        }
+    }
+    spinlock_unlock(&g_ksm_lock);
+}
+
+void ksm_idle(void)
+{
+    if (!g_config.enabled || __atomic_exchange_n(&g_scanning, true, __ATOMIC_ACQUIRE))
+    {
+        return;
+    }
+
+    uint64_t now = arch_get_timestamp();
+    spinlock_lock(&g_ksm_lock);
+    if (g_config.enabled && g_stats.spaces > 0 && now >= g_next_batch_ns)
+    {
+        scan_batch(now);
+        g_next_batch_ns = now + g_config.scan_interval_ns;
+    }
+    spinlock_unlock(&g_ksm_lock);
+    __atomic_store_n(&g_scanning, false, __ATOMIC_RELEASE);
+}
+
+int ksm_get_config(ksm_config_t *config)
+{
+    if (!config)
+    {
+        return -OR_EINVAL;
+    }
+    spinlock_lock(&g_ksm_lock);
+    *config = g_config;
+    spinlock_unlock(&g_ksm_lock);
+    return OR_OK;
+}
+
+int ksm_set_config(const ksm_config_t *config)
+{
+    if (!config || config->pages_to_scan == 0 || config->pages_to_scan > KSM_MAX_PAGES_TO_SCAN)
+    {
+        return -OR_EINVAL;
+    }
+    spinlock_lock(&g_ksm_lock);
+    bool toggled = (g_config.enabled != 0) != (config->enabled != 0);
+    g_config = *config;
+    g_config.enabled = config->enabled ? 1 : 0;
+    spinlock_unlock(&g_ksm_lock);
+
+    if (toggled)
+    {
+        kinfo("KSM: scanner %s, %u pages every %llu ms, minimum age %llu ms", config->enabled ? "on" : "off",
+              config->pages_to_scan, (unsigned long long)(config->scan_interval_ns / 1000000),
+              (unsigned long long)(config->min_age_ns / 1000000));
+    }
+    return OR_OK;
+}
+
+void ksm_get_stats(ksm_stats_t *stats)
+{
+    if (!stats)
+    {
+        return;
+    }
+    spinlock_lock(&g_ksm_lock);
+    *stats = g_stats;
+    stats->pages_shared = 0;
+    stats->pages_sharing = 0;
+    for (uint32_t i = 0; i < KSM_MAX_STABLE; i++)
+    {
+        // One share is the scanner's own
+        uint32_t mappings = g_stable[i].paddr ? pmm_page_mapcount(g_stable[i].paddr) - 1 : 0;
+        if (mappings > 0)
+        {
+            stats->pages_shared++;
+            stats->pages_sharing += mappings - 1;
+        }
+    }
+    spinlock_unlock(&g_ksm_lock);
+}
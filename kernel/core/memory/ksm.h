@@ -0,0 +1,99 @@
+/*
+ * Orion Operating System - Same-Page Merging
+ *
+ * Processes that host many similar workloads (guests, interpreters,
+ * forked servers) often hold the same page contents many times over.
+ * Areas a process marks with madvise(OR_MADV_MERGEABLE) are scanned in
+ * the background, when a CPU has nothing else to run, and anonymous
+ * pages that have stopped changing are merged: every copy is replaced by
+ * one read-only frame mapped copy-on-write, so the first write to it
+ * gives the writer its own page again through the usual fault path.
+ *
+ * Merging is opt-in twice over: nothing is scanned until the scanner is
+ * enabled with SYS_KSM_CTL, and only areas advised mergeable are. The
+ * scan rate and the minimum time a page must stay unchanged before it is
+ * merged are tunable; statistics report how much memory merging saves.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_KSM_H
+#define ORION_KSM_H
+
+#include <orion/types.h>
+#include <orion/mm.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Address spaces with mergeable areas
+#define KSM_MAX_SPACES 64
+
+// Merged frames the scanner can keep at once
+#define KSM_MAX_STABLE 4096
+
+// Defaults: off, 100 pages every 20 ms, pages unchanged for 2 seconds
+#define KSM_DEFAULT_PAGES_TO_SCAN 100
+#define KSM_DEFAULT_INTERVAL_NS 20000000ULL
+#define KSM_DEFAULT_MIN_AGE_NS 2000000000ULL
+
+// Largest batch, so a scan never holds a CPU for long
+#define KSM_MAX_PAGES_TO_SCAN 4096
+
+// SYS_KSM_CTL operations
+#define KSM_CTL_GET_CONFIG 1 // ksm_config_t *
+#define KSM_CTL_SET_CONFIG 2 // const ksm_config_t *
+#define KSM_CTL_STATS 3      // ksm_stats_t *
+
+    typedef struct ksm_config
+    {
+        uint32_t enabled;          // 0 stops scanning; merged pages stay merged
+        uint32_t pages_to_scan;    // Pages looked at per batch
+        uint64_t scan_interval_ns; // Pause between two batches
+        uint64_t min_age_ns;       // Time a page must stay unchanged to be merged
+    } ksm_config_t;
+
+    typedef struct ksm_stats
+    {
+        uint64_t spaces;         // Address spaces with mergeable areas
+        uint64_t pages_shared;   // Merged frames still mapped
+        uint64_t pages_sharing;  // Mappings of them beyond the first: pages saved
+        uint64_t pages_scanned;  // Pages looked at
+        uint64_t full_scans;     // Passes over every mergeable area
+        uint64_t merges;         // Pages replaced by a merged frame
+        uint64_t volatile_pages; // Candidates skipped for having changed
+        uint64_t released;       // Merged frames dropped once nothing shared them
+    } ksm_stats_t;
+
+    void ksm_init(void);
+
+    // Mark [start, start + length) of `space` mergeable or not. Making a
+    // range unmergeable gives its writable pages private copies again
+    int ksm_advise(vm_space_t *space, uint64_t start, uint64_t length, bool mergeable);
+
+    // A forked child inherits the mergeable areas of its parent
+    void ksm_fork_space(vm_space_t *parent, vm_space_t *child);
+
+    // Forget a space that is being destroyed
+    void ksm_release_space(vm_space_t *space);
+
+    // Scan one batch if the scanner is enabled and the interval has
+    // elapsed. Called from the idle loop; returns at once when another
+    // CPU is scanning
+    void ksm_idle(void);
+
+    int ksm_get_config(ksm_config_t *config);
+    int ksm_set_config(const ksm_config_t *config);
+    void ksm_get_stats(ksm_stats_t *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_KSM_H
@@ -8,7 +8,6 @@
 
 // Forward declarations for memory management types
 typedef struct vm_space vm_space_t;
-typedef struct page_ref page_ref_t;
 
 // Structure for heap blocks
 typedef struct heap_block
@@ -31,13 +30,58 @@ typedef struct slab
     struct slab *next;      // Next slab
 } slab_t;
 
+// Physical memory zones
+typedef enum pmm_zone
+{
+    PMM_ZONE_DMA32 = 0, // Below 4GB, for devices with 32-bit DMA
+    PMM_ZONE_NORMAL = 1,
+    PMM_ZONE_COUNT
+} pmm_zone_t;
+
+// Largest buddy block: 2^PMM_MAX_ORDER pages (64MB), enough for the
+// framebuffer of a 4K display in one contiguous DMA buffer
+#define PMM_MAX_ORDER 14
+
+// 2MB pages, each mapped by a single page directory entry
+#define HUGE_PAGE_SIZE (2ULL * 1024 * 1024)
+#define HUGE_PAGE_PAGES (HUGE_PAGE_SIZE / PAGE_SIZE)
+#define HUGE_PAGE_ORDER 9
+
+// pmm_alloc_frames() flags
+#define PMM_ALLOC_DMA32 (1 << 0) // Allocate from the DMA32 zone only
+#define PMM_ALLOC_ZERO (1 << 1)  // Clear the frames
+#define PMM_ALLOC_TRY (1 << 2)   // Opportunistic: normal zone only, fails quietly
+
+typedef struct pmm_zone_stats
+{
+    uint64_t total_pages;    // Pages managed by the buddy allocator
+    uint64_t free_pages;
+    uint64_t reserved_pages; // Pages never handed out
+    uint64_t allocations;
+    uint64_t frees;
+    uint64_t failures;
+    uint64_t free_blocks[PMM_MAX_ORDER + 1]; // Free blocks per order
+} pmm_zone_stats_t;
+
 // Physical Memory Manager (PMM) functions
+uint64_t pmm_early_alloc(uint64_t size, uint64_t align);
 void pmm_init(void);
 uint64_t pmm_alloc_page(void);
 void pmm_free_page(uint64_t phys_addr);
 uint64_t pmm_alloc_pages(uint64_t count);
 void pmm_free_pages(uint64_t phys_addr, uint64_t count);
+uint64_t pmm_alloc_frames(uint64_t count, uint32_t flags);
+void pmm_free_frames(uint64_t phys_addr, uint64_t count);
 void pmm_get_stats(uint64_t *total, uint64_t *free, uint64_t *used);
+uint64_t pmm_get_free_pages(void);
+int pmm_get_zone_stats(pmm_zone_t zone, pmm_zone_stats_t *stats);
+// Free 2MB blocks of `zone`, larger blocks counting as several
+uint64_t pmm_huge_blocks_free(pmm_zone_t zone);
+
+// Frames mapped by several address spaces: each share is dropped by one
+// pmm_free_page, the frame itself by the last
+int pmm_page_share(uint64_t phys_addr);
+uint32_t pmm_page_mapcount(uint64_t phys_addr);
 
 // Virtual Memory Manager (VMM) functions
 void vmm_init(void);
@@ -47,8 +91,18 @@ int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t fla
 int vmm_unmap_page(vm_space_t *space, uint64_t vaddr);
 int vmm_protect_page(vm_space_t *space, uint64_t vaddr, uint64_t new_flags);
 uint64_t vmm_alloc_pages(vm_space_t *space, uint64_t count, uint64_t flags);
+int vmm_alloc_pages_at(vm_space_t *space, uint64_t vaddr, size_t count, uint64_t flags);
+int vmm_alloc_dma_at(vm_space_t *space, uint64_t vaddr, size_t count, uint64_t flags);
 void vmm_free_pages(vm_space_t *space, uint64_t vaddr, uint64_t count);
 void vmm_destroy_space(vm_space_t *space);
+bool vmm_query_page(vm_space_t *space, uint64_t vaddr, uint64_t *paddr, uint64_t *flags);
+
+// Map the 2MB-aligned frames at `paddr` as one huge page at the 2MB-aligned
+// `vaddr`. Returns -OR_EEXIST when small pages are already mapped there.
+// Every 4KB operation on part of a huge page splits it into small pages
+// first, so the rest of the memory manager never has to know about them
+int vmm_map_huge(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t flags);
+int vmm_split_huge(vm_space_t *space, uint64_t vaddr);
 uint64_t mmu_virt_to_phys(uint64_t vaddr);
 bool mmu_is_valid_addr(uint64_t vaddr);
 void mmu_invalidate_page(uint64_t vaddr);
@@ -57,10 +111,29 @@ void mmu_flush_tlb(void);
 // Copy-on-Write (COW) functions
 int vmm_mark_cow(vm_space_t *space, uint64_t vaddr);
 int vmm_handle_cow_fault(vm_space_t *space, uint64_t vaddr);
-page_ref_t *vmm_get_page_ref(uint64_t paddr);
 void vmm_inc_page_ref(uint64_t paddr);
 void vmm_dec_page_ref(uint64_t paddr);
 
+// Copy of `parent` for fork: areas are duplicated, private pages are
+// shared read-only and copied on the first write of either side
+vm_space_t *vmm_fork_space(vm_space_t *parent);
+
+// Resident memory of an address space, in bytes. Shared pages count fully
+// in rss and `shared`, and in pss divided by the number of their mappings;
+// uss only holds pages no other space maps
+typedef struct vm_usage
+{
+    uint64_t rss;
+    uint64_t uss;
+    uint64_t pss;
+    uint64_t shared;
+} vm_usage_t;
+
+int vmm_get_usage(vm_space_t *space, vm_usage_t *usage);
+
+// Locked (non-swappable) memory
+int vmm_lock_range(vm_space_t *space, uint64_t vaddr, size_t count, bool lock);
+
 // Architecture-specific functions
 uint64_t read_cr3(void);
 
@@ -100,16 +173,25 @@ void *krealloc(void *ptr, uint64_t new_size);
 #define PAGE_FLAG_NO_CACHE (1 << 4)
 #define PAGE_FLAG_ACCESSED (1 << 5)
 #define PAGE_FLAG_DIRTY (1 << 6)
+#define PAGE_FLAG_HUGE (1 << 7)    // 2MB page, page directory entries only
 #define PAGE_FLAG_GLOBAL (1 << 8)
 #define PAGE_FLAG_COW (1 << 9)     // Copy-on-Write flag
 #define PAGE_FLAG_SHARED (1 << 10) // Shared page flag
-
-// COW-specific page management
-typedef struct page_ref
-{
-    atomic32_t ref_count;   // Reference counter for shared pages
-    uint64_t physical_addr; // Physical address
-    spinlock_t lock;        // Lock for atomic operations
-} page_ref_t;
+#define PAGE_FLAG_LOCKED (1 << 11) // Resident, never swapped nor dumped
+
+// madvise() advice values handled by the memory manager
+#define OR_MADV_NORMAL 0
+#define OR_MADV_LOCK 0x100        // Populate and pin pages, exclude from dumps
+#define OR_MADV_UNLOCK 0x101      // Release a previous OR_MADV_LOCK
+#define OR_MADV_MERGEABLE 0x102   // Let identical pages be merged, see ksm.h
+#define OR_MADV_UNMERGEABLE 0x103 // Stop merging, unshare merged pages
+
+// sys_vm_map() flags handled by the memory manager
+#define OR_MAP_DMA 0x100  // Locked buffer between guard pages, for device DMA
+#define OR_MAP_HUGE 0x200 // With OR_MAP_DMA: 2MB aligned, mapped with 2MB pages
+
+// sys_shm_create() placement flags
+#define OR_SHM_NODE 0x100     // Pages from the NUMA node in bits 16-23, any node if it has none free
+#define OR_SHM_NODE_SHIFT 16
 
 #endif // ORION_MM_H
@@ -0,0 +1,525 @@
+/*
+ * Orion Operating System - Out-of-Memory Handling
+ *
+ * The physical allocator reports every failed allocation here. Nothing
+ * in this file waits for memory: the allocation that failed still fails,
+ * and memory comes back later, when the policy agent has released some
+ * or the killed process is reaped. An episode therefore goes through a
+ * few windows, all measured from the first failure: until the agent's
+ * deadline only the agent acts, after it the kernel kills, and while a
+ * killed process has not been reaped yet nobody else is killed.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/scheduler.h>
+#include <orion/panic.h>
+#include "oom.h"
+
+extern int ipc_send_message(or_cap_t port, void *data, uint64_t size, uint64_t timeout_ns);
+
+#define SIGKILL 9
+
+typedef struct oom_group
+{
+    bool used;
+    uint32_t flags;
+    int32_t score_adj;
+    uint64_t timer_slack_ns;
+    char name[OOM_GROUP_NAME_MAX];
+
+    // Last evaluation
+    uint32_t members;
+    uint64_t pss;
+    int64_t badness;
+} oom_group_t;
+
+// Processes outside the default group; pid 0 marks a free slot
+typedef struct oom_member
+{
+    uint64_t pid;
+    uint32_t group;
+} oom_member_t;
+
+typedef struct oom_victim
+{
+    uint32_t group;
+    uint64_t pid; // Largest member of the group, 0 when nothing can be killed
+    uint64_t pss;
+} oom_victim_t;
+
+static oom_group_t g_groups[OOM_MAX_GROUPS];
+static oom_member_t g_members[OOM_MAX_MEMBERS];
+static oom_stats_t g_stats;
+static spinlock_t g_oom_lock = SPINLOCK_INIT;
+
+// Policy agent
+static uint64_t g_agent_pid;
+static or_cap_t g_agent_port;
+static uint64_t g_grace_ns = OOM_DEFAULT_GRACE_NS;
+
+// Current episode
+static bool g_running;      // An allocation failure is being handled
+static bool g_notified;     // The agent was told; it has until g_deadline_ns
+static uint64_t g_deadline_ns;
+static uint64_t g_kill_pid; // Killed, not reaped yet
+static uint64_t g_kill_ns;
+
+// ========================================
+// GROUPS
+// ========================================
+
+static void group_setup(uint32_t id, const char *name, int32_t score_adj, uint32_t flags)
+{
+    oom_group_t *group = &g_groups[id];
+    memset(group, 0, sizeof(*group));
+    group->used = true;
+    group->flags = flags;
+    group->score_adj = score_adj;
+    strncpy(group->name, name, sizeof(group->name) - 1);
+}
+
+static bool score_adj_valid(int32_t score_adj)
+{
+    return score_adj >= OOM_SCORE_ADJ_MIN && score_adj <= OOM_SCORE_ADJ_MAX;
+}
+
+// Caller holds g_oom_lock
+static int member_slot(uint64_t pid)
+{
+    for (int i = 0; i < OOM_MAX_MEMBERS; i++)
+    {
+        if (g_members[i].pid == pid)
+        {
+            return i;
+        }
+    }
+    return -1;
+}
+
+// Caller holds g_oom_lock
+static uint32_t group_of(uint64_t pid)
+{
+    int slot = pid ? member_slot(pid) : -1;
+    return slot >= 0 ? g_members[slot].group : OOM_GROUP_DEFAULT;
+}
+
+static bool group_exempt(const oom_group_t *group)
+{
+    return (group->flags & OOM_GROUP_PROTECTED) || group->score_adj == OOM_SCORE_ADJ_MIN;
+}
+
+static bool process_alive(const proc_info_t *info)
+{
+    return info->state != PROCESS_STATE_TERMINATED && info->state != PROCESS_STATE_ZOMBIE;
+}
+
+// Score every group and pick the victim. Badness is the share of memory
+// the group's members account for, in thousandths, plus its adjustment.
+// Caller holds g_oom_lock
+static void evaluate(oom_victim_t *victim)
+{
+    uint64_t total_pages = 0;
+    pmm_get_stats(&total_pages, NULL, NULL);
+    if (total_pages == 0)
+    {
+        total_pages = 1;
+    }
+
+    uint64_t largest_pss[OOM_MAX_GROUPS];
+    uint64_t largest_pid[OOM_MAX_GROUPS];
+    for (uint32_t i = 0; i < OOM_MAX_GROUPS; i++)
+    {
+        g_groups[i].members = 0;
+        g_groups[i].pss = 0;
+        g_groups[i].badness = -1;
+        largest_pss[i] = 0;
+        largest_pid[i] = 0;
+    }
+
+    proc_info_t info;
+    for (uint64_t after = 0; scheduler_get_process_info(after, &info) == OR_OK; after = info.pid)
+    {
+        if (!process_alive(&info))
+        {
+            continue;
+        }
+        uint32_t id = group_of(info.pid);
+        oom_group_t *group = &g_groups[id];
+        group->members++;
+        group->pss += info.pss;
+
+        // The agent is the one freeing memory; kernel threads own no pages
+        if (info.pid == g_agent_pid || info.pss == 0)
+        {
+            continue;
+        }
+        if (info.pss > largest_pss[id])
+        {
+            largest_pss[id] = info.pss;
+            largest_pid[id] = info.pid;
+        }
+    }
+
+    memset(victim, 0, sizeof(*victim));
+    int64_t worst = -1;
+    for (uint32_t i = 0; i < OOM_MAX_GROUPS; i++)
+    {
+        oom_group_t *group = &g_groups[i];
+        if (!group->used || group_exempt(group))
+        {
+            continue;
+        }
+        int64_t badness = (int64_t)((group->pss / PAGE_SIZE) * 1000 / total_pages) + group->score_adj;
+        group->badness = badness < 0 ? 0 : badness;
+        if (largest_pid[i] != 0 && group->badness > worst)
+        {
+            worst = group->badness;
+            victim->group = i;
+            victim->pid = largest_pid[i];
+            victim->pss = largest_pss[i];
+        }
+    }
+}
+
+// ========================================
+// PUBLIC API
+// ========================================
+
+void oom_init(void)
+{
+    spinlock_init(&g_oom_lock);
+    memset(g_groups, 0, sizeof(g_groups));
+    memset(g_members, 0, sizeof(g_members));
+    memset(&g_stats, 0, sizeof(g_stats));
+    group_setup(OOM_GROUP_DEFAULT, "default", 0, 0);
+    group_setup(OOM_GROUP_SYSTEM, "system", OOM_SCORE_ADJ_MIN, OOM_GROUP_PROTECTED);
+    kinfo("OOM: handler ready, agent grace period %llu ms", (unsigned long long)(g_grace_ns / 1000000));
+}
+
+int oom_group_create(const char *name, int32_t score_adj, uint32_t flags)
+{
+    if (!name || !name[0] || !score_adj_valid(score_adj) ||
+        (flags & ~(OOM_GROUP_PROTECTED | OOM_GROUP_KILL_ALL)))
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_oom_lock);
+    for (uint32_t i = 0; i < OOM_MAX_GROUPS; i++)
+    {
+        if (!g_groups[i].used)
+        {
+            group_setup(i, name, score_adj, flags);
+            spinlock_unlock(&g_oom_lock);
+            return (int)i;
+        }
+    }
+    spinlock_unlock(&g_oom_lock);
+    return -OR_ENOSPC;
+}
+
+int oom_group_set(uint32_t group, int32_t score_adj, uint32_t flags)
+{
+    if (group >= OOM_MAX_GROUPS || !score_adj_valid(score_adj) ||
+        (flags & ~(OOM_GROUP_PROTECTED | OOM_GROUP_KILL_ALL)))
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_oom_lock);
+    if (!g_groups[group].used)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return -OR_ENOENT;
+    }
+    g_groups[group].score_adj = score_adj;
+    g_groups[group].flags = flags;
+    spinlock_unlock(&g_oom_lock);
+    return OR_OK;
+}
+
+int oom_group_attach(uint32_t group, uint64_t pid)
+{
+    if (group >= OOM_MAX_GROUPS || pid == 0)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_oom_lock);
+    if (!g_groups[group].used)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return -OR_ENOENT;
+    }
+
+    int slot = member_slot(pid);
+    if (group == OOM_GROUP_DEFAULT)
+    {
+        // Default membership is the absence of an entry
+        if (slot >= 0)
+        {
+            g_members[slot].pid = 0;
+        }
+        spinlock_unlock(&g_oom_lock);
+        return OR_OK;
+    }
+    if (slot < 0)
+    {
+        slot = member_slot(0);
+        if (slot < 0)
+        {
+            spinlock_unlock(&g_oom_lock);
+            return -OR_ENOSPC;
+        }
+        g_members[slot].pid = pid;
+    }
+    g_members[slot].group = group;
+    spinlock_unlock(&g_oom_lock);
+    return OR_OK;
+}
+
+int oom_group_get_info(uint32_t group, oom_group_info_t *info)
+{
+    if (group >= OOM_MAX_GROUPS || !info)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_oom_lock);
+    if (!g_groups[group].used)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return -OR_ENOENT;
+    }
+    oom_victim_t victim;
+    evaluate(&victim);
+
+    const oom_group_t *entry = &g_groups[group];
+    memset(info, 0, sizeof(*info));
+    info->id = group;
+    info->flags = entry->flags;
+    info->score_adj = entry->score_adj;
+    info->members = entry->members;
+    info->pss = entry->pss;
+    info->badness = entry->badness;
+    memcpy(info->name, entry->name, sizeof(info->name));
+    info->timer_slack_ns = entry->timer_slack_ns;
+    spinlock_unlock(&g_oom_lock);
+    return OR_OK;
+}
+
+int oom_group_set_timer_slack(uint32_t group, uint64_t slack_ns)
+{
+    if (group >= OOM_MAX_GROUPS || slack_ns > OOM_TIMER_SLACK_MAX_NS)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_oom_lock);
+    if (!g_groups[group].used)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return -OR_ENOENT;
+    }
+    g_groups[group].timer_slack_ns = slack_ns;
+    spinlock_unlock(&g_oom_lock);
+    return OR_OK;
+}
+
+uint64_t oom_timer_slack(uint64_t pid)
+{
+    spinlock_lock(&g_oom_lock);
+    uint64_t slack_ns = g_groups[group_of(pid)].timer_slack_ns;
+    spinlock_unlock(&g_oom_lock);
+    return slack_ns;
+}
+
+int oom_set_agent(uint64_t pid, or_cap_t port, uint64_t grace_ns)
+{
+    spinlock_lock(&g_oom_lock);
+    if (g_agent_port != 0 && g_agent_pid != pid)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return -OR_EBUSY;
+    }
+    g_agent_pid = port ? pid : 0;
+    g_agent_port = port;
+    g_grace_ns = grace_ns ? grace_ns : OOM_DEFAULT_GRACE_NS;
+    g_notified = false;
+    grace_ns = g_grace_ns;
+    spinlock_unlock(&g_oom_lock);
+
+    if (port)
+    {
+        kinfo("OOM: policy agent is PID %llu, grace period %llu ms", (unsigned long long)pid,
+              (unsigned long long)(grace_ns / 1000000));
+    }
+    return OR_OK;
+}
+
+void oom_out_of_memory(uint64_t pages)
+{
+    spinlock_lock(&g_oom_lock);
+    g_stats.events++;
+    // Sending the event or writing the dump may allocate, and fail, too
+    if (g_running)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return;
+    }
+
+    uint64_t now = arch_get_timestamp();
+    if (g_kill_pid && now < g_kill_ns + g_grace_ns)
+    {
+        // The last victim's memory has not come back yet
+        spinlock_unlock(&g_oom_lock);
+        return;
+    }
+    g_kill_pid = 0;
+    if (g_notified && now >= g_deadline_ns + g_grace_ns)
+    {
+        // The agent dealt with the last episode; this is a new one
+        g_notified = false;
+    }
+    if (g_notified && now < g_deadline_ns)
+    {
+        spinlock_unlock(&g_oom_lock);
+        return;
+    }
+
+    g_running = true;
+    oom_victim_t victim;
+    evaluate(&victim);
+
+    oom_event_t event;
+    memset(&event, 0, sizeof(event));
+    event.group = victim.group;
+    event.pid = victim.pid;
+    event.free_pages = pmm_get_free_pages();
+    event.wanted_pages = pages;
+    or_cap_t port = g_agent_port;
+
+    // The agent gets the first chance
+    if (port && !g_notified)
+    {
+        g_notified = true;
+        g_deadline_ns = now + g_grace_ns;
+        event.type = OOM_EVENT_LOW_MEMORY;
+        event.deadline_ns = g_deadline_ns;
+        spinlock_unlock(&g_oom_lock);
+
+        if (ipc_send_message(port, &event, sizeof(event), 0) == OR_OK)
+        {
+            spinlock_lock(&g_oom_lock);
+            g_stats.notifications++;
+            g_running = false;
+            spinlock_unlock(&g_oom_lock);
+            kwarn("OOM: %llu pages wanted, %llu free; policy agent notified",
+                  (unsigned long long)pages, (unsigned long long)event.free_pages);
+            return;
+        }
+        // An agent that cannot be reached does not hold the kernel back
+        kwarn("OOM: policy agent unreachable");
+        spinlock_lock(&g_oom_lock);
+    }
+
+    if (victim.pid == 0)
+    {
+        g_stats.no_victim++;
+        g_running = false;
+        spinlock_unlock(&g_oom_lock);
+        kerror("OOM: %llu pages wanted, nothing left to kill", (unsigned long long)pages);
+        return;
+    }
+
+    bool kill_all = (g_groups[victim.group].flags & OOM_GROUP_KILL_ALL) != 0;
+    char group_name[OOM_GROUP_NAME_MAX];
+    memcpy(group_name, g_groups[victim.group].name, sizeof(group_name));
+    g_kill_pid = victim.pid;
+    g_kill_ns = now;
+    g_notified = false;
+    spinlock_unlock(&g_oom_lock);
+
+    kerror("OOM: killing PID %llu (%llu KiB) of group %s%s", (unsigned long long)victim.pid,
+           (unsigned long long)(victim.pss / 1024), group_name, kill_all ? " and the rest of its group" : "");
+    crash_record_fault(CRASH_FAULT_OOM, 0, victim.pid, group_name);
+    save_core_dump(__FILE__, __LINE__, __func__, "Out of memory");
+
+    uint64_t kills = 0;
+    if (kill_all)
+    {
+        proc_info_t info;
+        for (uint64_t after = 0; scheduler_get_process_info(after, &info) == OR_OK; after = info.pid)
+        {
+            spinlock_lock(&g_oom_lock);
+            bool member = group_of(info.pid) == victim.group && info.pid != g_agent_pid;
+            spinlock_unlock(&g_oom_lock);
+            process_t *process = member ? scheduler_find_process(info.pid) : NULL;
+            if (process && signal_send(process, SIGKILL) == OR_OK)
+            {
+                kills++;
+            }
+        }
+    }
+    else
+    {
+        process_t *process = scheduler_find_process(victim.pid);
+        if (process && signal_send(process, SIGKILL) == OR_OK)
+        {
+            kills++;
+        }
+    }
+
+    spinlock_lock(&g_oom_lock);
+    g_stats.kills += kills;
+    g_running = false;
+    port = g_agent_port;
+    spinlock_unlock(&g_oom_lock);
+
+    if (port)
+    {
+        event.type = OOM_EVENT_KILLED;
+        event.deadline_ns = 0;
+        ipc_send_message(port, &event, sizeof(event), 0);
+    }
+}
+
+void oom_process_exit(uint64_t pid)
+{
+    spinlock_lock(&g_oom_lock);
+    int slot = member_slot(pid);
+    if (slot >= 0)
+    {
+        g_members[slot].pid = 0;
+    }
+    if (g_kill_pid == pid)
+    {
+        g_kill_pid = 0;
+    }
+    if (g_agent_pid == pid)
+    {
+        g_agent_pid = 0;
+        g_agent_port = 0;
+        g_notified = false;
+    }
+    spinlock_unlock(&g_oom_lock);
+}
+
+void oom_get_stats(oom_stats_t *stats)
+{
+    if (!stats)
+    {
+        return;
+    }
+    spinlock_lock(&g_oom_lock);
+    *stats = g_stats;
+    spinlock_unlock(&g_oom_lock);
+}
@@ -0,0 +1,138 @@
+/*
+ * Orion Operating System - Out-of-Memory Handling
+ *
+ * Processes belong to resource groups, each with a score adjustment and
+ * protection flags. When the physical allocator runs dry, every group is
+ * scored by the memory of its members (PSS, so pages shared after fork
+ * are not counted twice) plus its adjustment, and the largest member of
+ * the worst group is picked. A registered userspace policy agent is told
+ * first and given a grace period to free memory its own way; only if
+ * memory is still short after it does the kernel capture a crash dump
+ * and kill the victim. Protected groups, such as the one holding the fs
+ * and net servers, are never picked.
+ *
+ * Groups also carry a timer slack: sleeps of their members may end that
+ * much later than asked, rounded so that members going to sleep around
+ * the same time wake together. Giving background groups a generous slack
+ * on battery cuts the number of wakeups without touching the programs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_OOM_H
+#define ORION_OOM_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define OOM_MAX_GROUPS 32
+#define OOM_MAX_MEMBERS 256
+#define OOM_GROUP_NAME_MAX 24
+
+// Built-in groups: processes not attached elsewhere, and system servers
+#define OOM_GROUP_DEFAULT 0
+#define OOM_GROUP_SYSTEM 1
+
+// Group flags
+#define OOM_GROUP_PROTECTED (1 << 0) // Never selected as victim
+#define OOM_GROUP_KILL_ALL (1 << 1)  // Kill every member, not just the largest
+
+// Score adjustment range; OOM_SCORE_ADJ_MIN also exempts the group
+#define OOM_SCORE_ADJ_MIN (-1000)
+#define OOM_SCORE_ADJ_MAX 1000
+
+// Time the policy agent gets before the kernel kills
+#define OOM_DEFAULT_GRACE_NS 2000000000ULL
+
+// Largest timer slack of a group
+#define OOM_TIMER_SLACK_MAX_NS 1000000000ULL
+
+// SYS_OOM_CTL operations
+#define OOM_CTL_SET_AGENT 1    // port, grace_ns: the caller becomes the agent
+#define OOM_CTL_GROUP_CREATE 2 // name, score_adj, flags
+#define OOM_CTL_GROUP_SET 3    // group, score_adj, flags
+#define OOM_CTL_GROUP_ATTACH 4 // group, pid (0 for the caller)
+#define OOM_CTL_GROUP_INFO 5   // group, oom_group_info_t *
+#define OOM_CTL_STATS 6        // oom_stats_t *
+#define OOM_CTL_GROUP_SLACK 7  // group, slack_ns
+
+    // Message sent to the policy agent's port
+    typedef enum oom_event_type
+    {
+        OOM_EVENT_LOW_MEMORY = 1, // Memory ran out; free some before `deadline_ns`
+        OOM_EVENT_KILLED,         // The kernel killed `pid` itself
+    } oom_event_type_t;
+
+    typedef struct oom_event
+    {
+        uint32_t type;         // oom_event_type_t
+        uint32_t group;        // Group the kernel would pick, or picked
+        uint64_t pid;          // Process the kernel would kill, or killed
+        uint64_t free_pages;   // Free physical pages when the event was raised
+        uint64_t wanted_pages; // Size of the allocation that failed
+        uint64_t deadline_ns;  // LOW_MEMORY: when the kernel steps in
+    } oom_event_t;
+
+    typedef struct oom_group_info
+    {
+        uint32_t id;
+        uint32_t flags;
+        int32_t score_adj;
+        uint32_t members;
+        uint64_t pss;     // Bytes, summed over members
+        int64_t badness; // Score at the last evaluation, -1 when exempt
+        char name[OOM_GROUP_NAME_MAX];
+        uint64_t timer_slack_ns;
+    } oom_group_info_t;
+
+    typedef struct oom_stats
+    {
+        uint64_t events;        // Allocation failures reported
+        uint64_t notifications; // LOW_MEMORY events delivered to the agent
+        uint64_t kills;         // Processes killed by the kernel
+        uint64_t no_victim;     // Failures with nothing left to kill
+    } oom_stats_t;
+
+    void oom_init(void);
+
+    // Groups. oom_group_create returns the new group's id or a negative
+    // error; a process belongs to one group, attaching moves it
+    int oom_group_create(const char *name, int32_t score_adj, uint32_t flags);
+    int oom_group_set(uint32_t group, int32_t score_adj, uint32_t flags);
+    int oom_group_attach(uint32_t group, uint64_t pid);
+    int oom_group_get_info(uint32_t group, oom_group_info_t *info);
+
+    // Timer slack of a group, 0 for none; at most OOM_TIMER_SLACK_MAX_NS
+    int oom_group_set_timer_slack(uint32_t group, uint64_t slack_ns);
+
+    // Timer slack of the group `pid` belongs to
+    uint64_t oom_timer_slack(uint64_t pid);
+
+    // Register the policy agent: LOW_MEMORY events go to `port` and the
+    // agent has `grace_ns` to act on them. Port 0 unregisters it. The
+    // agent's own process is never selected
+    int oom_set_agent(uint64_t pid, or_cap_t port, uint64_t grace_ns);
+
+    // Called by the physical allocator when an allocation of `pages`
+    // failed. Never blocks: memory comes back once the agent or the
+    // killed process has released it
+    void oom_out_of_memory(uint64_t pages);
+
+    // Forget a process that is being destroyed
+    void oom_process_exit(uint64_t pid);
+
+    void oom_get_stats(oom_stats_t *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_OOM_H
@@ -1,81 +1,472 @@
 // Physical Memory Manager (PMM) for Orion
+//
+// Before pmm_init, pmm_early_alloc hands out memory with a bump pointer
+// walking the available regions of the boot memory map. pmm_init then
+// sizes a frame descriptor array from the map (allocated with that same
+// bump pointer) and switches to a buddy allocator: free blocks of 2^order
+// frames sit on per-order lists of the zone they belong to, split on
+// allocation and merged with their buddy on free. Frames below 4GB form
+// the DMA32 zone, kept for devices limited to 32-bit addresses; ordinary
+// allocations only fall back to it when the normal zone is exhausted.
+// Frames the loader did not report as available, low memory, the kernel
+// image, boot modules and early allocations are reserved and never enter
+// the free lists.
+//
+// Every allocated frame is tracked on its own, so pages of a contiguous
+// allocation can be freed one by one and still coalesce. An allocated
+// frame mapped by several address spaces (copy-on-write after fork)
+// counts its extra mappings; freeing it drops one of them until the last
+// mapping goes.
 #include <orion/types.h>
 #include <orion/mm.h>
 #include <orion/kernel.h>
+#include <orion/bootinfo.h>
+#include <orion/oom.h>
 
-// Bitmap for physical pages (for 4GB = 1M pages = 128KB bitmap)
-#define MAX_PAGES       (1024 * 1024)  // 4GB / 4KB
-#define BITMAP_SIZE     (MAX_PAGES / 8)
+#define PMM_NO_FRAME       0xFFFFFFFFu
+#define PMM_NO_ORDER       0xFF
+#define PMM_MAX_FRAMES     0xFFFFFFFEull
 
-static uint8_t page_bitmap[BITMAP_SIZE];
+// Frames below this index belong to the DMA32 zone
+#define PMM_DMA32_FRAMES   ((4ull << 30) / PAGE_SIZE)
+
+// Ranges handed out by the early allocator, reserved by pmm_init
+#define PMM_EARLY_MAX_RANGES 16
+
+// Frame states
+#define FRAME_RESERVED     0
+#define FRAME_FREE         1 // Part of a free block; `order` set on its first frame only
+#define FRAME_ALLOCATED    2
+
+typedef struct pmm_frame {
+    uint32_t next;  // Free list links, first frame of a free block only
+    uint32_t prev;
+    uint8_t state;
+    uint8_t order;
+    uint16_t shares; // Mappings beyond the first, allocated frames only
+} pmm_frame_t;
+
+typedef struct pmm_zone_data {
+    uint32_t free_list[PMM_MAX_ORDER + 1];
+    pmm_zone_stats_t stats;
+} pmm_zone_data_t;
+
+typedef struct pmm_early_range {
+    uint64_t base;
+    uint64_t end;
+} pmm_early_range_t;
+
+static pmm_frame_t* frames = NULL;
 static uint64_t total_pages = 0;
 static uint64_t free_pages = 0;
-static uint64_t first_free_page = 0;
+static pmm_zone_data_t zones[PMM_ZONE_COUNT];
+static spinlock_t pmm_lock = SPINLOCK_INIT;
 static bool pmm_initialized = false;
 
-// Mark a page as used
-static void set_page_used(uint64_t page_num) {
-    if (page_num >= MAX_PAGES) return;
-    
-    uint64_t byte_index = page_num / 8;
-    uint8_t bit_index = page_num % 8;
-    
-    if (!(page_bitmap[byte_index] & (1 << bit_index))) {
-        page_bitmap[byte_index] |= (1 << bit_index);
-        free_pages--;
-    }
-}
-
-// Mark a page as free
-static void set_page_free(uint64_t page_num) {
-    if (page_num >= MAX_PAGES) return;
-    
-    uint64_t byte_index = page_num / 8;
-    uint8_t bit_index = page_num % 8;
-    
-    if (page_bitmap[byte_index] & (1 << bit_index)) {
-        page_bitmap[byte_index] &= ~(1 << bit_index);
-        free_pages++;
-        
-        if (page_num < first_free_page) {
-            first_free_page = page_num;
+static pmm_early_range_t early_ranges[PMM_EARLY_MAX_RANGES];
+static uint32_t early_range_count = 0;
+static uint64_t early_cursor = BOOT_LOW_MEMORY_RESERVED;
+
+static const char* const zone_names[PMM_ZONE_COUNT] = {"DMA32", "Normal"};
+
+// ========================================
+// EARLY BOOT ALLOCATOR
+// ========================================
+
+// End of the first range overlapping [base, end) that early allocations
+// must avoid, 0 when there is none
+static uint64_t early_conflict(const boot_info_t* boot, uint64_t base, uint64_t end) {
+    if (base < boot->kernel_end && end > boot->kernel_base) {
+        return boot->kernel_end;
+    }
+    for (uint32_t i = 0; i < boot->module_count; i++) {
+        uint64_t module_end = boot->modules[i].base + boot->modules[i].size;
+        if (base < module_end && end > boot->modules[i].base) {
+            return module_end;
+        }
+    }
+    for (uint32_t i = 0; i < boot->memory_count; i++) {
+        const boot_memory_region_t* region = &boot->memory[i];
+        uint64_t region_end = region->base + region->length;
+        if (region->type != ORION_MEMORY_AVAILABLE && base < region_end && end > region->base) {
+            return region_end;
+        }
+    }
+    return 0;
+}
+
+static bool early_record(uint64_t base, uint64_t end) {
+    pmm_early_range_t* last = early_range_count ? &early_ranges[early_range_count - 1] : NULL;
+    if (last && last->end == base) {
+        last->end = end;
+        return true;
+    }
+    if (early_range_count == PMM_EARLY_MAX_RANGES) {
+        return false;
+    }
+    early_ranges[early_range_count].base = base;
+    early_ranges[early_range_count].end = end;
+    early_range_count++;
+    return true;
+}
+
+// Allocate physically contiguous boot memory. Only meant for structures
+// needed before the PMM runs; once it does, requests are served from the
+// buddy allocator. Early allocations are never freed
+uint64_t pmm_early_alloc(uint64_t size, uint64_t align) {
+    if (size == 0) {
+        return 0;
+    }
+    if (pmm_initialized) {
+        return pmm_alloc_frames((size + PAGE_SIZE - 1) / PAGE_SIZE, PMM_ALLOC_ZERO);
+    }
+
+    const boot_info_t* boot = boot_info_get();
+    if (!boot) {
+        return 0;
+    }
+    if (align < PAGE_SIZE) {
+        align = PAGE_SIZE;
+    }
+    size = ROUND_UP(size, PAGE_SIZE);
+
+    // The map is sorted by base, so the cursor only ever moves forward
+    for (uint32_t i = 0; i < boot->memory_count; i++) {
+        const boot_memory_region_t* region = &boot->memory[i];
+        uint64_t region_end = region->base + region->length;
+        if (region->type != ORION_MEMORY_AVAILABLE || region_end <= early_cursor) {
+            continue;
+        }
+
+        uint64_t base = ROUND_UP(MAX(early_cursor, region->base), align);
+        while (base + size <= region_end) {
+            uint64_t conflict = early_conflict(boot, base, base + size);
+            if (!conflict) {
+                if (!early_record(base, base + size)) {
+                    kerror("PMM: too many early allocations");
+                    return 0;
+                }
+                early_cursor = base + size;
+                memset((void*)(uintptr_t)base, 0, size);
+                return base;
+            }
+            base = ROUND_UP(conflict, align);
+        }
+    }
+
+    kerror("PMM: early allocation of %llu bytes failed", (unsigned long long)size);
+    return 0;
+}
+
+// ========================================
+// BUDDY ALLOCATOR
+// ========================================
+
+static pmm_zone_t frame_zone(uint64_t index) {
+    return index < PMM_DMA32_FRAMES ? PMM_ZONE_DMA32 : PMM_ZONE_NORMAL;
+}
+
+static void list_insert(uint64_t index, uint8_t order) {
+    pmm_zone_data_t* zone = &zones[frame_zone(index)];
+    pmm_frame_t* frame = &frames[index];
+
+    frame->state = FRAME_FREE;
+    frame->order = order;
+    frame->prev = PMM_NO_FRAME;
+    frame->next = zone->free_list[order];
+    if (frame->next != PMM_NO_FRAME) {
+        frames[frame->next].prev = (uint32_t)index;
+    }
+    zone->free_list[order] = (uint32_t)index;
+    zone->stats.free_blocks[order]++;
+}
+
+static void list_remove(uint64_t index) {
+    pmm_zone_data_t* zone = &zones[frame_zone(index)];
+    pmm_frame_t* frame = &frames[index];
+
+    if (frame->prev != PMM_NO_FRAME) {
+        frames[frame->prev].next = frame->next;
+    } else {
+        zone->free_list[frame->order] = frame->next;
+    }
+    if (frame->next != PMM_NO_FRAME) {
+        frames[frame->next].prev = frame->prev;
+    }
+    zone->stats.free_blocks[frame->order]--;
+    frame->order = PMM_NO_ORDER;
+}
+
+// Return one allocated frame, merging it with free buddies
+static void frame_release(uint64_t index) {
+    pmm_zone_t zone = frame_zone(index);
+    uint8_t order = 0;
+
+    frames[index].state = FRAME_FREE;
+    frames[index].order = PMM_NO_ORDER;
+    zones[zone].stats.free_pages++;
+    free_pages++;
+
+    while (order < PMM_MAX_ORDER) {
+        uint64_t buddy = index ^ (1ull << order);
+        if (buddy >= total_pages || frame_zone(buddy) != zone || frames[buddy].state != FRAME_FREE ||
+            frames[buddy].order != order) {
+            break;
         }
+        list_remove(buddy);
+        index = MIN(index, buddy);
+        order++;
     }
+    list_insert(index, order);
 }
 
-// Check if a page is free
-static bool is_page_free(uint64_t page_num) {
-    if (page_num >= MAX_PAGES) return false;
-    
-    uint64_t byte_index = page_num / 8;
-    uint8_t bit_index = page_num % 8;
-    
-    return !(page_bitmap[byte_index] & (1 << bit_index));
+// Take a free block of 2^order frames from `zone`, splitting a larger one
+static uint64_t block_take(pmm_zone_t zone, uint8_t order) {
+    uint8_t found = order;
+    while (found <= PMM_MAX_ORDER && zones[zone].free_list[found] == PMM_NO_FRAME) {
+        found++;
+    }
+    if (found > PMM_MAX_ORDER) {
+        return PMM_NO_FRAME;
+    }
+
+    uint64_t index = zones[zone].free_list[found];
+    list_remove(index);
+    // Give the upper halves back until the block has the requested size
+    while (found > order) {
+        found--;
+        list_insert(index + (1ull << found), found);
+    }
+    return index;
 }
 
-    // Initialize PMM with basic configuration
+static uint8_t order_for(uint64_t count) {
+    uint8_t order = 0;
+    while ((1ull << order) < count) {
+        order++;
+    }
+    return order;
+}
+
+// Allocate `count` physically contiguous frames. PMM_ALLOC_DMA32 keeps the
+// allocation below 4GB; otherwise the normal zone is tried first
+uint64_t pmm_alloc_frames(uint64_t count, uint32_t flags) {
+    if (!pmm_initialized || count == 0) {
+        return 0;
+    }
+
+    uint8_t order = order_for(count);
+    if (order > PMM_MAX_ORDER) {
+        kerror("PMM: %llu contiguous pages exceed the largest block", (unsigned long long)count);
+        return 0;
+    }
+
+    spinlock_lock(&pmm_lock);
+    pmm_zone_t zone = (flags & PMM_ALLOC_DMA32) ? PMM_ZONE_DMA32 : PMM_ZONE_NORMAL;
+    uint64_t index = block_take(zone, order);
+    if (index == PMM_NO_FRAME && zone == PMM_ZONE_NORMAL && !(flags & PMM_ALLOC_TRY)) {
+        zone = PMM_ZONE_DMA32;
+        index = block_take(zone, order);
+    }
+    if (index == PMM_NO_FRAME && (flags & PMM_ALLOC_TRY)) {
+        // The caller has a fallback; this is not memory running out
+        spinlock_unlock(&pmm_lock);
+        return 0;
+    }
+    if (index == PMM_NO_FRAME) {
+        zones[(flags & PMM_ALLOC_DMA32) ? PMM_ZONE_DMA32 : PMM_ZONE_NORMAL].stats.failures++;
+        spinlock_unlock(&pmm_lock);
+        kerror("PMM: no free block of %llu pages", (unsigned long long)(1ull << order));
+        // DMA32 failures are about the zone, not about memory running out
+        if (!(flags & PMM_ALLOC_DMA32)) {
+            oom_out_of_memory(1ull << order);
+        }
+        return 0;
+    }
+
+    for (uint64_t i = 0; i < (1ull << order); i++) {
+        frames[index + i].state = FRAME_ALLOCATED;
+        frames[index + i].order = PMM_NO_ORDER;
+        frames[index + i].shares = 0;
+    }
+    zones[zone].stats.free_pages -= 1ull << order;
+    free_pages -= 1ull << order;
+    // Only `count` frames were asked for; the rest of the block goes back
+    for (uint64_t i = count; i < (1ull << order); i++) {
+        frame_release(index + i);
+    }
+    zones[zone].stats.allocations++;
+    spinlock_unlock(&pmm_lock);
+
+    uint64_t phys_addr = index * PAGE_SIZE;
+    if (flags & PMM_ALLOC_ZERO) {
+        memset((void*)(uintptr_t)phys_addr, 0, count * PAGE_SIZE);
+    }
+    return phys_addr;
+}
+
+// Free `count` frames from pmm_alloc_frames or pmm_alloc_pages, in one go
+// or in pieces. Shared frames only lose a mapping
+void pmm_free_frames(uint64_t phys_addr, uint64_t count) {
+    if (!pmm_initialized || count == 0) {
+        return;
+    }
+
+    if (!IS_ALIGNED(phys_addr, PAGE_SIZE)) {
+        kerror("Attempt to free unaligned address: 0x%p", (void*)phys_addr);
+        return;
+    }
+
+    uint64_t first = phys_addr / PAGE_SIZE;
+    if (first >= total_pages || count > total_pages - first) {
+        kerror("Attempt to free invalid pages: %llu+%llu", (unsigned long long)first,
+               (unsigned long long)count);
+        return;
+    }
+
+    spinlock_lock(&pmm_lock);
+    for (uint64_t index = first; index < first + count; index++) {
+        if (frames[index].state != FRAME_ALLOCATED) {
+            kwarning("Attempt to free page %llu which is not allocated", (unsigned long long)index);
+            continue;
+        }
+        if (frames[index].shares) {
+            frames[index].shares--;
+            continue;
+        }
+        frame_release(index);
+    }
+    zones[frame_zone(first)].stats.frees++;
+    spinlock_unlock(&pmm_lock);
+}
+
+// ========================================
+// SHARED FRAMES
+// ========================================
+
+static pmm_frame_t* allocated_frame(uint64_t phys_addr) {
+    uint64_t index = phys_addr / PAGE_SIZE;
+    if (!pmm_initialized || index >= total_pages || frames[index].state != FRAME_ALLOCATED) {
+        return NULL;
+    }
+    return &frames[index];
+}
+
+// Record one more mapping of an allocated frame
+int pmm_page_share(uint64_t phys_addr) {
+    spinlock_lock(&pmm_lock);
+    pmm_frame_t* frame = allocated_frame(phys_addr);
+    int result = -OR_EINVAL;
+    if (frame && frame->shares < 0xFFFF) {
+        frame->shares++;
+        result = OR_OK;
+    } else if (frame) {
+        result = -OR_EBUSY;
+    }
+    spinlock_unlock(&pmm_lock);
+    return result;
+}
+
+// Number of mappings of a frame; 0 when it is not allocated memory (free,
+// reserved or device memory)
+uint32_t pmm_page_mapcount(uint64_t phys_addr) {
+    spinlock_lock(&pmm_lock);
+    pmm_frame_t* frame = allocated_frame(phys_addr);
+    uint32_t count = frame ? (uint32_t)frame->shares + 1 : 0;
+    spinlock_unlock(&pmm_lock);
+    return count;
+}
+
+// ========================================
+// INITIALIZATION
+// ========================================
+
+// Mark every frame overlapping [base, base + length) as reserved
+static void pmm_reserve_range(uint64_t base, uint64_t length) {
+    if (length == 0) return;
+
+    uint64_t first = base / PAGE_SIZE;
+    uint64_t last = (base + length + PAGE_SIZE - 1) / PAGE_SIZE;
+    for (uint64_t page = first; page < last && page < total_pages; page++) {
+        frames[page].state = FRAME_RESERVED;
+    }
+}
+
+// Initialize PMM from the memory map handed over by the loader
 void pmm_init(void) {
     kinfo("Initializing Physical Memory Manager");
-    
-    // Mark all pages as free initially
-    for (size_t i = 0; i < BITMAP_SIZE; i++) {
-        page_bitmap[i] = 0;
-    }
-    
-    // Simplified configuration: assume 512MB usable RAM
-    // Pages 0-255 (1MB): reserved for boot/kernel
-    // Pages 256-131071 (512MB-1MB): free
-    total_pages = 131072; // 512MB / 4KB
-    free_pages = total_pages - 256;
-    first_free_page = 256;
-    
-    // Mark first pages as used (boot + kernel)
-    for (uint64_t i = 0; i < 256; i++) {
-        set_page_used(i);
-    }
-    
+
+    const boot_info_t* boot = boot_info_get();
+    if (!boot) {
+        kernel_panic("PMM: no boot memory map");
+    }
+
+    total_pages = boot->memory_top / PAGE_SIZE;
+    if (total_pages > PMM_MAX_FRAMES) {
+        kwarn("PMM: only the first %llu GB of memory are managed",
+              (unsigned long long)(PMM_MAX_FRAMES * PAGE_SIZE >> 30));
+        total_pages = PMM_MAX_FRAMES;
+    }
+
+    // The descriptor array is the last early allocation; it starts with
+    // every frame reserved
+    frames = (pmm_frame_t*)(uintptr_t)pmm_early_alloc(total_pages * sizeof(pmm_frame_t), PAGE_SIZE);
+    if (!frames) {
+        kernel_panic("PMM: no memory for the frame array");
+    }
+
+    for (int zone = 0; zone < PMM_ZONE_COUNT; zone++) {
+        memset(&zones[zone], 0, sizeof(zones[zone]));
+        for (int order = 0; order <= PMM_MAX_ORDER; order++) {
+            zones[zone].free_list[order] = PMM_NO_FRAME;
+        }
+    }
+
+    // Whole pages of available memory are candidates
+    for (uint32_t i = 0; i < boot->memory_count; i++) {
+        const boot_memory_region_t* region = &boot->memory[i];
+        if (region->type != ORION_MEMORY_AVAILABLE) continue;
+
+        uint64_t first = ROUND_UP(region->base, PAGE_SIZE) / PAGE_SIZE;
+        uint64_t last = (region->base + region->length) / PAGE_SIZE;
+        for (uint64_t page = first; page < last && page < total_pages; page++) {
+            frames[page].state = FRAME_ALLOCATED;
+        }
+    }
+
+    // Entries may overlap: anything the loader reports as not available
+    // wins. Loader and ACPI reclaimable memory stays reserved until its
+    // contents have been consumed
+    for (uint32_t i = 0; i < boot->memory_count; i++) {
+        const boot_memory_region_t* region = &boot->memory[i];
+        if (region->type != ORION_MEMORY_AVAILABLE) {
+            pmm_reserve_range(region->base, region->length);
+        }
+    }
+
+    // Low memory, the kernel image, boot modules and early allocations
+    pmm_reserve_range(0, BOOT_LOW_MEMORY_RESERVED);
+    pmm_reserve_range(boot->kernel_base, boot->kernel_end - boot->kernel_base);
+    for (uint32_t i = 0; i < boot->module_count; i++) {
+        pmm_reserve_range(boot->modules[i].base, boot->modules[i].size);
+    }
+    for (uint32_t i = 0; i < early_range_count; i++) {
+        pmm_reserve_range(early_ranges[i].base, early_ranges[i].end - early_ranges[i].base);
+    }
+
+    // Hand the remaining candidates to the buddy allocator
+    free_pages = 0;
+    for (uint64_t page = 0; page < total_pages; page++) {
+        if (frames[page].state == FRAME_ALLOCATED) {
+            zones[frame_zone(page)].stats.total_pages++;
+            frame_release(page);
+        } else if (frames[page].state == FRAME_RESERVED) {
+            zones[frame_zone(page)].stats.reserved_pages++;
+        }
+    }
+
     pmm_initialized = true;
-    
+
     kinfo("PMM initialized:");
     kinfo("  Total pages: %llu (%llu MB)",
           (unsigned long long)total_pages,
@@ -83,119 +474,83 @@ void pmm_init(void) {
     kinfo("  Free pages: %llu (%llu MB)",
           (unsigned long long)free_pages,
           (unsigned long long)(free_pages * PAGE_SIZE / 1024 / 1024));
+    for (int zone = 0; zone < PMM_ZONE_COUNT; zone++) {
+        kinfo("  Zone %s: %llu pages free, %llu reserved", zone_names[zone],
+              (unsigned long long)zones[zone].stats.free_pages,
+              (unsigned long long)zones[zone].stats.reserved_pages);
+    }
 }
 
+// ========================================
+// PAGE INTERFACE
+// ========================================
+
 // Allocate a physical page
 uint64_t pmm_alloc_page(void) {
-    if (!pmm_initialized || free_pages == 0) {
-        return 0; // No free pages
-    }
-    
-    // Find first free page
-    for (uint64_t page = first_free_page; page < total_pages; page++) {
-        if (is_page_free(page)) {
-            set_page_used(page);
-            
-            // Update first_free_page
-            if (page == first_free_page) {
-                while (first_free_page < total_pages && !is_page_free(first_free_page)) {
-                    first_free_page++;
-                }
-            }
-            
-            kdebug("Allocated physical page %llu (addr 0x%p)",
-                   (unsigned long long)page,
-                   (void*)(page * PAGE_SIZE));
-            
-            return page * PAGE_SIZE; // Return physical address
-        }
+    uint64_t phys_addr = pmm_alloc_frames(1, 0);
+
+    if (phys_addr) {
+        kdebug("Allocated physical page %llu (addr 0x%p)",
+               (unsigned long long)(phys_addr / PAGE_SIZE),
+               (void*)phys_addr);
     }
-    
-    kerror("No free pages available");
-    return 0;
+    return phys_addr;
 }
 
 // Free a physical page
 void pmm_free_page(uint64_t phys_addr) {
-    if (!pmm_initialized) {
-        return;
-    }
-    
-    if (!IS_ALIGNED(phys_addr, PAGE_SIZE)) {
-        kerror("Attempt to free unaligned address: 0x%p", (void*)phys_addr);
-        return;
-    }
-    
-    uint64_t page_num = phys_addr / PAGE_SIZE;
-    if (page_num >= total_pages) {
-        kerror("Attempt to free invalid page: %llu", (unsigned long long)page_num);
-        return;
-    }
-    
-    if (is_page_free(page_num)) {
-        kwarning("Attempt to free already free page: %llu", (unsigned long long)page_num);
-        return;
-    }
-    
-    set_page_free(page_num);
-    
+    pmm_free_frames(phys_addr, 1);
+
     kdebug("Freed physical page %llu (addr 0x%p)",
-           (unsigned long long)page_num, (void*)phys_addr);
+           (unsigned long long)(phys_addr / PAGE_SIZE), (void*)phys_addr);
 }
 
 // Allocate multiple contiguous pages
 uint64_t pmm_alloc_pages(size_t count) {
-    if (!pmm_initialized || count == 0) {
-        return 0;
-    }
-    
-    if (count == 1) {
-        return pmm_alloc_page();
-    }
-    
-    // Find 'count' contiguous free pages
-    for (uint64_t start_page = first_free_page; start_page + count <= total_pages; start_page++) {
-        bool all_free = true;
-        
-        // Check if all pages are free
-        for (size_t i = 0; i < count; i++) {
-            if (!is_page_free(start_page + i)) {
-                all_free = false;
-                break;
-            }
-        }
-        
-        if (all_free) {
-            // Allocate all pages
-            for (size_t i = 0; i < count; i++) {
-                set_page_used(start_page + i);
-            }
-            
-            kdebug("Allocated %llu contiguous pages starting at %llu",
-                   (unsigned long long)count, (unsigned long long)start_page);
-            
-            return start_page * PAGE_SIZE;
-        }
-    }
-    
-    kerror("Could not allocate %llu contiguous pages", (unsigned long long)count);
-    return 0;
+    return pmm_alloc_frames(count, 0);
 }
 
 // Free multiple contiguous pages
 void pmm_free_pages(uint64_t phys_addr, size_t count) {
-    if (!pmm_initialized || count == 0) {
-        return;
-    }
-    
-    for (size_t i = 0; i < count; i++) {
-        pmm_free_page(phys_addr + i * PAGE_SIZE);
-    }
+    pmm_free_frames(phys_addr, count);
 }
 
+// ========================================
+// STATISTICS
+// ========================================
+
 // Get memory statistics
 void pmm_get_stats(uint64_t* total, uint64_t* free, uint64_t* used) {
     if (total) *total = total_pages;
     if (free) *free = free_pages;
     if (used) *used = total_pages - free_pages;
 }
+
+uint64_t pmm_get_free_pages(void) {
+    return free_pages;
+}
+
+uint64_t pmm_huge_blocks_free(pmm_zone_t zone) {
+    if (zone >= PMM_ZONE_COUNT) {
+        return 0;
+    }
+
+    uint64_t blocks = 0;
+    spinlock_lock(&pmm_lock);
+    for (int order = HUGE_PAGE_ORDER; order <= PMM_MAX_ORDER; order++) {
+        blocks += zones[zone].stats.free_blocks[order] << (order - HUGE_PAGE_ORDER);
+    }
+    spinlock_unlock(&pmm_lock);
+    return blocks;
+}
+
+int pmm_get_zone_stats(pmm_zone_t zone, pmm_zone_stats_t* stats) {
+    if (zone >= PMM_ZONE_COUNT || !stats) {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&pmm_lock);
+    *stats = zones[zone].stats;
+    spinlock_unlock(&pmm_lock);
+    return OR_OK;
+}
@@ -0,0 +1,719 @@
+/*
+ * Orion Operating System - Virtual Memory Areas
+ *
+ * Areas of an address space live in an AVL tree keyed by start address;
+ * areas never overlap, so the tree also orders them by end address. The
+ * tree lock only covers the tree itself: faults copy the area they hit
+ * and allocate and map outside the lock.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/aslr.h>
+#include "vma.h"
+
+// ========================================
+// AVL TREE
+// ========================================
+
+static int node_height(const vma_t *node)
+{
+    return node ? node->height : 0;
+}
+
+static void node_update(vma_t *node)
+{
+    node->height = MAX(node_height(node->left), node_height(node->right)) + 1;
+}
+
+static vma_t *rotate_right(vma_t *node)
+{
+    vma_t *pivot = node->left;
+    node->left = pivot->right;
+    pivot->right = node;
+    node_update(node);
+    node_update(pivot);
+    return pivot;
+}
+
+static vma_t *rotate_left(vma_t *node)
+{
+    vma_t *pivot = node->right;
+    node->right = pivot->left;
+    pivot->left = node;
+    node_update(node);
+    node_update(pivot);
+    return pivot;
+}
+
+static vma_t *node_balance(vma_t *node)
+{
+    node_update(node);
+    int balance = node_height(node->left) - node_height(node->right);
+    if (balance > 1)
+    {
+        if (node_height(node->left->left) < node_height(node->left->right))
+        {
+            node->left = rotate_left(node->left);
+        }
+        return rotate_right(node);
+    }
+    if (balance < -1)
+    {
+        if (node_height(node->right->right) < node_height(node->right->left))
+        {
+            node->right = rotate_right(node->right);
+        }
+        return rotate_left(node);
+    }
+    return node;
+}
+
+static vma_t *node_insert(vma_t *root, vma_t *node)
+{
+    if (!root)
+    {
+        return node;
+    }
+    if (node->start < root->start)
+    {
+        root->left = node_insert(root->left, node);
+    }
+    else
+    {
+        root->right = node_insert(root->right, node);
+    }
+    return node_balance(root);
+}
+
+// Detach the leftmost node of `root` into *min
+static vma_t *node_remove_min(vma_t *root, vma_t **min)
+{
+    if (!root->left)
+    {
+        *min = root;
+        return root->right;
+    }
+    root->left = node_remove_min(root->left, min);
+    return node_balance(root);
+}
+
+// Detach the node starting at `start`; the node itself is not freed
+static vma_t *node_remove(vma_t *root, uint64_t start)
+{
+    if (!root)
+    {
+        return NULL;
+    }
+    if (start < root->start)
+    {
+        root->left = node_remove(root->left, start);
+    }
+    else if (start > root->start)
+    {
+        root->right = node_remove(root->right, start);
+    }
+    else
+    {
+        if (!root->left || !root->right)
+        {
+            return root->left ? root->left : root->right;
+        }
+        vma_t *successor;
+        vma_t *right = node_remove_min(root->right, &successor);
+        successor->left = root->left;
+        successor->right = right;
+        return node_balance(successor);
+    }
+    return node_balance(root);
+}
+
+// First area ending after `addr`, i.e. containing it or the next one above
+static vma_t *node_first_after(vma_t *root, uint64_t addr)
+{
+    vma_t *best = NULL;
+    while (root)
+    {
+        if (root->end > addr)
+        {
+            best = root;
+            root = root->left;
+        }
+        else
+        {
+            root = root->right;
+        }
+    }
+    return best;
+}
+
+static vma_t *node_find(vma_t *root, uint64_t addr)
+{
+    vma_t *area = node_first_after(root, addr);
+    return (area && area->start <= addr) ? area : NULL;
+}
+
+// ========================================
+// TREE HELPERS (tree lock held)
+// ========================================
+
+static bool tree_overlaps(vma_tree_t *tree, uint64_t start, uint64_t end)
+{
+    vma_t *area = node_first_after(tree->root, start);
+    return area && area->start < end;
+}
+
+static int tree_add(vma_tree_t *tree, const vma_t *template)
+{
+    vma_t *area = (vma_t *)kmalloc(sizeof(vma_t));
+    if (!area)
+    {
+        return -OR_ENOMEM;
+    }
+    *area = *template;
+    area->left = NULL;
+    area->right = NULL;
+    area->height = 1;
+    tree->root = node_insert(tree->root, area);
+    tree->count++;
+    return OR_OK;
+}
+
+// Split the area containing `addr` so that an area starts exactly there
+static int tree_split(vma_tree_t *tree, uint64_t addr)
+{
+    vma_t *area = node_find(tree->root, addr);
+    if (!area || area->start == addr)
+    {
+        return OR_OK;
+    }
+
+    vma_t upper = *area;
+    upper.start = addr;
+    upper.offset = area->offset + (addr - area->start);
+    int result = tree_add(tree, &upper);
+    if (result == OR_OK)
+    {
+        // The tree is keyed by start, which does not change here
+        area->end = addr;
+    }
+    return result;
+}
+
+// ========================================
+// PAGE POPULATION
+// ========================================
+
+// Map the page holding `addr` in `area`; `page` is page aligned
+static int area_fill_page(vm_space_t *space, vma_tree_t *tree, const vma_t *area, uint64_t page)
+{
+    uint64_t offset = area->offset + (page - area->start);
+
+    if (area->type == VMA_DEVICE || area->type == VMA_SHARED)
+    {
+        uint64_t flags = area->prot | (area->type == VMA_DEVICE ? PAGE_FLAG_NO_CACHE : 0);
+        int result = vmm_map_page(space, page, area->backing + offset, flags);
+        if (result == OR_OK)
+        {
+            tree->stats.device++;
+        }
+        return result;
+    }
+
+    uint64_t paddr = pmm_alloc_frames(1, PMM_ALLOC_ZERO);
+    if (!paddr)
+    {
+        return -OR_ENOMEM;
+    }
+    if (area->type == VMA_FILE && area->pager)
+    {
+        int result = area->pager(area, offset, (void *)(uintptr_t)paddr);
+        if (result != OR_OK)
+        {
+            pmm_free_page(paddr);
+            return result;
+        }
+    }
+
+    uint64_t flags = area->prot | ((area->flags & VMA_LOCKED) ? PAGE_FLAG_LOCKED : 0);
+    int result = vmm_map_page(space, page, paddr, flags);
+    if (result != OR_OK)
+    {
+        pmm_free_page(paddr);
+        return result;
+    }
+    if (area->type == VMA_FILE)
+    {
+        tree->stats.file++;
+    }
+    else
+    {
+        tree->stats.anonymous++;
+    }
+    return OR_OK;
+}
+
+// Populate the 2MB block at `block` of a file area with one huge page,
+// filled by the pager the way its 512 small pages would be
+static int area_fill_huge(vm_space_t *space, const vma_t *area, uint64_t block)
+{
+    // Transparent use never takes the last blocks drivers need for DMA
+    if (pmm_huge_blocks_free(PMM_ZONE_NORMAL) <= VMA_HUGE_RESERVE)
+    {
+        return -OR_ENOMEM;
+    }
+    uint64_t paddr = pmm_alloc_frames(HUGE_PAGE_PAGES, PMM_ALLOC_ZERO | PMM_ALLOC_TRY);
+    if (!paddr)
+    {
+        return -OR_ENOMEM;
+    }
+
+    uint64_t offset = area->offset + (block - area->start);
+    for (uint64_t i = 0; i < HUGE_PAGE_PAGES; i++)
+    {
+        int result = area->pager(area, offset + i * PAGE_SIZE, (void *)(uintptr_t)(paddr + i * PAGE_SIZE));
+        if (result != OR_OK)
+        {
+            pmm_free_frames(paddr, HUGE_PAGE_PAGES);
+            return result;
+        }
+    }
+
+    uint64_t flags = area->prot | ((area->flags & VMA_LOCKED) ? PAGE_FLAG_LOCKED : 0);
+    int result = vmm_map_huge(space, block, paddr, flags);
+    if (result != OR_OK)
+    {
+        pmm_free_frames(paddr, HUGE_PAGE_PAGES);
+    }
+    return result;
+}
+
+// Whether the fault at `addr` in `area` should populate its whole 2MB
+// block: a large file area, a block entirely inside it, and a reader
+// that starts at the beginning or carries on from the previous fault
+static bool area_wants_huge(const vma_t *area, uint64_t addr)
+{
+    uint64_t block = ROUND_DOWN(addr, HUGE_PAGE_SIZE);
+    if (area->type != VMA_FILE || !area->pager || area->end - area->start < VMA_HUGE_MIN)
+    {
+        return false;
+    }
+    if (block < area->start || block + HUGE_PAGE_SIZE > area->end)
+    {
+        return false;
+    }
+    return block == area->start || area->next_fault == block;
+}
+
+// ========================================
+// PUBLIC INTERFACE
+// ========================================
+
+void vma_tree_init(vma_tree_t *tree)
+{
+    memset(tree, 0, sizeof(*tree));
+    spinlock_init(&tree->lock);
+}
+
+int vma_insert(vm_space_t *space, const vma_t *area)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree || !area || area->start >= area->end || !IS_ALIGNED(area->start, PAGE_SIZE) ||
+        !IS_ALIGNED(area->end, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&tree->lock);
+    int result = tree_overlaps(tree, area->start, area->end) ? -OR_EEXIST : tree_add(tree, area);
+    spinlock_unlock(&tree->lock);
+    return result;
+}
+
+uint64_t vma_map_anonymous(vm_space_t *space, uint64_t lo, uint64_t hi, uint64_t length, uint64_t prot)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree || length == 0)
+    {
+        return 0;
+    }
+    length = ROUND_UP(length, PAGE_SIZE);
+
+    vma_t area = {0};
+    area.prot = prot;
+    area.type = VMA_ANONYMOUS;
+    area.flags = VMA_LAZY;
+
+    spinlock_lock(&tree->lock);
+    // Random placement first, then the lowest gap that fits
+    for (int attempt = 0; attempt <= ASLR_PLACEMENT_TRIES; attempt++)
+    {
+        uint64_t start = aslr_random_base(lo, hi, length);
+        if (attempt == ASLR_PLACEMENT_TRIES)
+        {
+            start = ROUND_UP(lo, PAGE_SIZE);
+            for (vma_t *next = node_first_after(tree->root, start); next && next->start < start + length;
+                 next = node_first_after(tree->root, start))
+            {
+                start = next->end;
+            }
+        }
+        if (start + length > hi || tree_overlaps(tree, start, start + length) ||
+            aslr_range_has_guard(space, start, start + length))
+        {
+            continue;
+        }
+
+        area.start = start;
+        area.end = start + length;
+        int result = tree_add(tree, &area);
+        spinlock_unlock(&tree->lock);
+        return result == OR_OK ? start : 0;
+    }
+    spinlock_unlock(&tree->lock);
+
+    kerror("vma_map_anonymous: no gap of %llu bytes in 0x%p - 0x%p", (unsigned long long)length, (void *)lo,
+           (void *)hi);
+    return 0;
+}
+
+int vma_unmap(vm_space_t *space, uint64_t start, uint64_t length)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree || length == 0 || !IS_ALIGNED(start, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+    uint64_t end = start + ROUND_UP(length, PAGE_SIZE);
+
+    spinlock_lock(&tree->lock);
+    int result = tree_split(tree, start);
+    if (result == OR_OK)
+    {
+        result = tree_split(tree, end);
+    }
+    if (result != OR_OK)
+    {
+        spinlock_unlock(&tree->lock);
+        return result;
+    }
+
+    // Pages outside every area predate area tracking and are freed too
+    for (uint64_t page = start; page < end; page += PAGE_SIZE)
+    {
+        uint64_t paddr;
+        if (!vmm_query_page(space, page, &paddr, NULL))
+        {
+            continue;
+        }
+        vma_t *area = node_find(tree->root, page);
+        bool owned = !area || area->type == VMA_ANONYMOUS || area->type == VMA_FILE;
+        if (vmm_unmap_page(space, page) == OR_OK && owned && paddr)
+        {
+            pmm_free_page(paddr);
+        }
+    }
+
+    vma_t *area;
+    while ((area = node_first_after(tree->root, start)) && area->start < end)
+    {
+        tree->root = node_remove(tree->root, area->start);
+        tree->count--;
+        kfree(area);
+    }
+    spinlock_unlock(&tree->lock);
+    return OR_OK;
+}
+
+int vma_protect(vm_space_t *space, uint64_t start, uint64_t length, uint64_t prot)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree || length == 0 || !IS_ALIGNED(start, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+    uint64_t end = start + ROUND_UP(length, PAGE_SIZE);
+
+    spinlock_lock(&tree->lock);
+    // The whole range must be mapped, without holes
+    uint64_t covered = start;
+    for (vma_t *area = node_find(tree->root, start); area && area->start == covered && covered < end;
+         area = node_first_after(tree->root, covered))
+    {
+        covered = area->end;
+    }
+    if (covered < end)
+    {
+        spinlock_unlock(&tree->lock);
+        return -OR_EFAULT;
+    }
+
+    int result = tree_split(tree, start);
+    if (result == OR_OK)
+    {
+        result = tree_split(tree, end);
+    }
+    if (result != OR_OK)
+    {
+        spinlock_unlock(&tree->lock);
+        return result;
+    }
+
+    for (vma_t *area = node_find(tree->root, start); area && area->start < end;
+         area = node_first_after(tree->root, area->end))
+    {
+        area->prot = prot;
+        uint64_t extra = (area->type == VMA_DEVICE ? PAGE_FLAG_NO_CACHE : 0) |
+                         ((area->flags & VMA_LOCKED) ? PAGE_FLAG_LOCKED : 0);
+        for (uint64_t page = area->start; page < area->end; page += PAGE_SIZE)
+        {
+            uint64_t flags;
+            if (!vmm_query_page(space, page, NULL, &flags))
+            {
+                continue;
+            }
+            // Pages still shared after fork stay read-only until copied
+            uint64_t cow = (flags & PAGE_FLAG_COW) ? PAGE_FLAG_COW : 0;
+            vmm_protect_page(space, page, ((prot | extra) & ~(cow ? PAGE_FLAG_WRITE : 0)) | cow);
+        }
+    }
+    spinlock_unlock(&tree->lock);
+    return OR_OK;
+}
+
+int vma_set_flags(vm_space_t *space, uint64_t start, uint64_t length, uint32_t set, uint32_t clear)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree || length == 0 || !IS_ALIGNED(start, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+    uint64_t end = start + ROUND_UP(length, PAGE_SIZE);
+
+    spinlock_lock(&tree->lock);
+    uint64_t covered = start;
+    for (vma_t *area = node_find(tree->root, start); area && area->start == covered && covered < end;
+         area = node_first_after(tree->root, covered))
+    {
+        covered = area->end;
+    }
+    int result = covered < end ? -OR_EFAULT : tree_split(tree, start);
+    if (result == OR_OK)
+    {
+        result = tree_split(tree, end);
+    }
+    if (result == OR_OK)
+    {
+        for (vma_t *area = node_find(tree->root, start); area && area->start < end;
+             area = node_first_after(tree->root, area->end))
+        {
+            area->flags = (area->flags & ~clear) | set;
+        }
+    }
+    spinlock_unlock(&tree->lock);
+    return result;
+}
+
+int vma_populate(vm_space_t *space, uint64_t start, uint64_t length)
+{
+    if (!space || length == 0 || !IS_ALIGNED(start, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+
+    for (uint64_t page = start; page < start + length; page += PAGE_SIZE)
+    {
+        int result = vma_handle_fault(space, page, 0, vmm_query_page(space, page, NULL, NULL));
+        if (result != OR_OK && result != -OR_EEXIST)
+        {
+            return result;
+        }
+    }
+    return OR_OK;
+}
+
+bool vma_lookup(vm_space_t *space, uint64_t addr, vma_t *out)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree)
+    {
+        return false;
+    }
+
+    spinlock_lock(&tree->lock);
+    vma_t *area = node_find(tree->root, addr);
+    if (area && out)
+    {
+        *out = *area;
+        out->left = NULL;
+        out->right = NULL;
+    }
+    spinlock_unlock(&tree->lock);
+    return area != NULL;
+}
+
+bool vma_next(vm_space_t *space, uint64_t addr, vma_t *out)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree)
+    {
+        return false;
+    }
+
+    spinlock_lock(&tree->lock);
+    vma_t *area = node_first_after(tree->root, addr);
+    if (area && out)
+    {
+        *out = *area;
+        out->left = NULL;
+        out->right = NULL;
+    }
+    spinlock_unlock(&tree->lock);
+    return area != NULL;
+}
+
+bool vma_overlaps(vm_space_t *space, uint64_t start, uint64_t end)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree)
+    {
+        return false;
+    }
+
+    spinlock_lock(&tree->lock);
+    bool overlaps = tree_overlaps(tree, start, end);
+    spinlock_unlock(&tree->lock);
+    return overlaps;
+}
+
+int vma_handle_fault(vm_space_t *space, uint64_t addr, uint64_t access, bool present)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree)
+    {
+        return -OR_EFAULT;
+    }
+
+    spinlock_lock(&tree->lock);
+    tree->stats.faults++;
+    vma_t *found = node_find(tree->root, addr);
+    vma_t area;
+    bool huge = false;
+    if (found)
+    {
+        area = *found;
+        huge = !present && area_wants_huge(found, addr);
+        found->next_fault = huge ? ROUND_DOWN(addr, HUGE_PAGE_SIZE) + HUGE_PAGE_SIZE
+                                 : ROUND_DOWN(addr, PAGE_SIZE) + PAGE_SIZE;
+    }
+    spinlock_unlock(&tree->lock);
+
+    if (!found)
+    {
+        tree->stats.unmapped++;
+        return -OR_EFAULT;
+    }
+    if (((access & VM_FLAG_WRITE) && !(area.prot & VM_FLAG_WRITE)) ||
+        ((access & VM_FLAG_EXEC) && !(area.prot & VM_FLAG_EXEC)))
+    {
+        tree->stats.protection++;
+        return -OR_EPERM;
+    }
+    if (present)
+    {
+        return -OR_EEXIST;
+    }
+
+    if (huge)
+    {
+        if (area_fill_huge(space, &area, ROUND_DOWN(addr, HUGE_PAGE_SIZE)) == OR_OK)
+        {
+            tree->stats.huge++;
+            return OR_OK;
+        }
+        tree->stats.huge_fallback++;
+    }
+
+    int result = area_fill_page(space, tree, &area, ROUND_DOWN(addr, PAGE_SIZE));
+    if (result != OR_OK)
+    {
+        tree->stats.failed++;
+        kerror("vma_handle_fault: cannot populate 0x%p (error %d)", (void *)addr, result);
+    }
+    return result;
+}
+
+void vma_get_fault_stats(vm_space_t *space, vm_fault_stats_t *out)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!out)
+    {
+        return;
+    }
+    if (!tree)
+    {
+        memset(out, 0, sizeof(*out));
+        return;
+    }
+
+    spinlock_lock(&tree->lock);
+    *out = tree->stats;
+    spinlock_unlock(&tree->lock);
+}
+
+int vma_clone(vm_space_t *parent, vm_space_t *child)
+{
+    vma_tree_t *from = parent ? vmm_space_vmas(parent) : NULL;
+    vma_tree_t *to = child ? vmm_space_vmas(child) : NULL;
+    if (!from || !to || to->root)
+    {
+        return -OR_EINVAL;
+    }
+
+    // In order: the lowest area above the previous one each time
+    int result = OR_OK;
+    spinlock_lock(&from->lock);
+    for (vma_t *area = node_first_after(from->root, 0); area && result == OR_OK;
+         area = node_first_after(from->root, area->end))
+    {
+        if (!(area->flags & VMA_DMA))
+        {
+            result = tree_add(to, area);
+        }
+    }
+    spinlock_unlock(&from->lock);
+    return result;
+}
+
+void vma_release_space(vm_space_t *space)
+{
+    vma_tree_t *tree = space ? vmm_space_vmas(space) : NULL;
+    if (!tree)
+    {
+        return;
+    }
+
+    spinlock_lock(&tree->lock);
+    vma_t *area;
+    while ((area = tree->root) != NULL)
+    {
+        if (area->type == VMA_DEVICE || area->type == VMA_SHARED)
+        {
+            for (uint64_t page = area->start; page < area->end; page += PAGE_SIZE)
+            {
+                vmm_unmap_page(space, page);
+            }
+        }
+        tree->root = node_remove(tree->root, area->start);
+        kfree(area);
+    }
+    tree->count = 0;
+    spinlock_unlock(&tree->lock);
+}
@@ -0,0 +1,171 @@
+/*
+ * Orion Operating System - Virtual Memory Areas
+ *
+ * Every address space keeps a tree of the areas mapped into it: what
+ * backs each range (anonymous memory, a file, device memory or a shared
+ * memory object), its protection, and whether its pages are allocated
+ * up front or on first touch. The page fault handler resolves faults in
+ * lazily populated areas from here, mprotect and munmap split areas at
+ * the requested boundaries, and the placement of new mappings looks for
+ * gaps between areas rather than for unmapped pages, which would miss
+ * areas that have not been touched yet.
+ *
+ * Large file areas read front to back are populated 2MB at a time with
+ * huge pages, as long as free 2MB blocks are plentiful; otherwise, or
+ * when the access pattern is random, they fall back to 4KB pages.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_VMA_H
+#define ORION_VMA_H
+
+#include <orion/types.h>
+#include <orion/mm.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Area flags
+#define VMA_LAZY (1 << 0)      // Pages are allocated on first touch
+#define VMA_LOCKED (1 << 1)    // Resident for the lifetime of the area
+#define VMA_DMA (1 << 2)       // Physically contiguous, below 4GB
+#define VMA_MERGEABLE (1 << 3) // Scanned for identical pages, see ksm.h
+
+// Window new mappings without a fixed address are placed in
+#define VMA_MMAP_BASE 0x0000200000000000ULL
+#define VMA_MMAP_RANGE (1ULL << 40)
+
+// File areas at least this large are candidates for huge pages
+#define VMA_HUGE_MIN (2 * HUGE_PAGE_SIZE)
+// Free 2MB blocks left to drivers before areas stop using huge pages
+#define VMA_HUGE_RESERVE 16
+
+    typedef enum vma_type
+    {
+        VMA_ANONYMOUS = 1, // Zero-filled memory
+        VMA_FILE,          // Filled by the area's pager, private copy
+        VMA_DEVICE,        // Device memory at `backing`, uncached, never freed
+        VMA_SHARED,        // Shared memory object pages at `backing`, never freed
+    } vma_type_t;
+
+    struct vma;
+
+    // Fill `page` with the contents at `offset` of a file-backed area.
+    // Returns OR_OK or a negative error
+    typedef int (*vma_pager_t)(const struct vma *area, uint64_t offset, void *page);
+
+    typedef struct vma
+    {
+        uint64_t start; // First byte, page aligned
+        uint64_t end;   // One past the last byte, page aligned
+        uint64_t prot;  // VM_FLAG_* protection
+        vma_type_t type;
+        uint32_t flags; // VMA_*
+
+        // Device or shared memory: physical address of the first page.
+        // File: opaque handle for the pager
+        uint64_t backing;
+        uint64_t offset; // Offset of `start` within the backing object
+        vma_pager_t pager;
+
+        // Owned by vma.c: the page after the last one a fault populated,
+        // to recognize sequential access
+        uint64_t next_fault;
+
+        // Tree links, owned by vma.c
+        struct vma *left;
+        struct vma *right;
+        int height;
+    } vma_t;
+
+    typedef struct vm_fault_stats
+    {
+        uint64_t faults;        // Faults looked up in the area tree
+        uint64_t anonymous;     // Zero pages allocated on first touch
+        uint64_t file;          // Pages filled by a pager
+        uint64_t device;        // Device or shared pages mapped
+        uint64_t protection;    // Accesses the area does not allow
+        uint64_t unmapped;      // Faults outside every area
+        uint64_t failed;        // Out of memory or pager errors
+        uint64_t copies;        // Private copies of pages shared by fork
+        uint64_t huge;          // 2MB pages populated in one go
+        uint64_t huge_fallback; // Huge page candidates populated 4KB at a time
+    } vm_fault_stats_t;
+
+    // Per address space area tree
+    typedef struct vma_tree
+    {
+        vma_t *root;
+        uint64_t count;
+        spinlock_t lock;
+        vm_fault_stats_t stats;
+    } vma_tree_t;
+
+    void vma_tree_init(vma_tree_t *tree);
+
+    // Area tree of an address space (defined by vmm.c, which owns vm_space_t)
+    vma_tree_t *vmm_space_vmas(vm_space_t *space);
+
+    // Record an area described by `area` (links ignored). The range must
+    // not overlap an existing area. Non-lazy areas are expected to be
+    // mapped by the caller
+    int vma_insert(vm_space_t *space, const vma_t *area);
+
+    // Place a lazily populated anonymous area of `length` bytes inside
+    // [lo, hi) at a random free address. Returns its start, or 0
+    uint64_t vma_map_anonymous(vm_space_t *space, uint64_t lo, uint64_t hi, uint64_t length, uint64_t prot);
+
+    // Remove [start, start + length) from the area tree, splitting areas
+    // that straddle the bounds, and unmap it. Anonymous and file pages are
+    // freed, device and shared pages are left to their owner
+    int vma_unmap(vm_space_t *space, uint64_t start, uint64_t length);
+
+    // Change the protection of [start, start + length), which must be
+    // entirely covered by areas; populated pages are updated in place
+    int vma_protect(vm_space_t *space, uint64_t start, uint64_t length, uint64_t prot);
+
+    // Set and clear VMA_* flags on [start, start + length), which must be
+    // entirely covered by areas; pages are left as they are
+    int vma_set_flags(vm_space_t *space, uint64_t start, uint64_t length, uint32_t set, uint32_t clear);
+
+    // Populate every page of [start, start + length) now
+    int vma_populate(vm_space_t *space, uint64_t start, uint64_t length);
+
+    // Copy of the area containing `addr`
+    bool vma_lookup(vm_space_t *space, uint64_t addr, vma_t *out);
+
+    // Copy of the lowest area ending above `addr`, which may start above it
+    bool vma_next(vm_space_t *space, uint64_t addr, vma_t *out);
+
+    // Whether any area overlaps [start, end)
+    bool vma_overlaps(vm_space_t *space, uint64_t start, uint64_t end);
+
+    // Resolve a page fault at `addr`; `access` holds VM_FLAG_WRITE and
+    // VM_FLAG_EXEC for write and instruction fetch faults. Returns OR_OK
+    // once the page is mapped, -OR_EEXIST for an allowed access to a page
+    // that is already present (copy-on-write), -OR_EPERM for an access the
+    // area forbids and -OR_EFAULT outside every area
+    int vma_handle_fault(vm_space_t *space, uint64_t addr, uint64_t access, bool present);
+
+    void vma_get_fault_stats(vm_space_t *space, vm_fault_stats_t *out);
+
+    // Copy the areas of `parent` into the empty tree of `child`, except DMA
+    // buffers, which a forked process does not inherit
+    int vma_clone(vm_space_t *parent, vm_space_t *child);
+
+    // Drop every area when the address space goes away; device and shared
+    // pages are unmapped first so the page table walk does not free them
+    void vma_release_space(vm_space_t *space);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_VMA_H
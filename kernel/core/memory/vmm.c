@@ -15,6 +15,9 @@
 #include <orion/mm.h>
 #include <orion/kernel.h>
 #include <orion/security.h>
+#include <orion/aslr.h>
+#include <orion/vma.h>
+#include <orion/ksm.h>
 
 // ========================================
 // CONSTANTS AND CONFIGURATION
@@ -43,6 +46,7 @@
 #define PTE_CACHE_DISABLE (1ULL << 3)
 #define PTE_ACCESSED (1ULL << 5)
 #define PTE_DIRTY (1ULL << 6)
+#define PTE_HUGE (1ULL << 7) // Page directory entry mapping a 2MB page
 #define PTE_GLOBAL (1ULL << 8)
 #define PTE_NX (1ULL << 63)
 
@@ -88,12 +92,9 @@ typedef struct vm_space
 
     // Add the missing free_bitmap field
     uint64_t *free_bitmap; // Free page bitmap for fast allocation
-} vm_space_t;
 
-// COW page reference tracking
-#define MAX_PAGE_REFS 65536
-static page_ref_t page_refs[MAX_PAGE_REFS];
-static spinlock_t page_refs_lock = SPINLOCK_INIT;
+    vma_tree_t vmas; // Mapped areas, see vma.c
+} vm_space_t;
 
 // Global kernel space
 static vm_space_enhanced_t kernel_space_enhanced;
@@ -114,6 +115,7 @@ void vmm_init(void)
     kernel_space.start_addr = KERNEL_SPACE_START;
     kernel_space.end_addr = KERNEL_SPACE_END;
     kernel_space.is_kernel = true;
+    vma_tree_init(&kernel_space.vmas);
 
     vmm_initialized = true;
 
@@ -161,6 +163,7 @@ vm_space_t *vmm_create_space(bool is_kernel)
     user_space->start_addr = USER_SPACE_START;
     user_space->end_addr = USER_SPACE_END;
     user_space->is_kernel = false;
+    vma_tree_init(&user_space->vmas);
 
     // Copy kernel entries from current PML4 to the new one
     uint64_t *current_pml4 = (uint64_t *)(kernel_space.pml4_phys);
@@ -185,31 +188,13 @@ vm_space_t *vmm_create_space(bool is_kernel)
     return user_space;
 }
 
-// Map a virtual page to a physical page
-int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t flags)
+// Page directory entry covering `vaddr`, creating the PDPT and page
+// directory on the way. NULL when out of memory
+static uint64_t *pde_create(vm_space_t *space, uint64_t vaddr)
 {
-    if (!vmm_initialized || !space)
-    {
-        return -OR_EINVAL;
-    }
-
-    // Check alignment
-    if (!IS_ALIGNED(vaddr, PAGE_SIZE) || !IS_ALIGNED(paddr, PAGE_SIZE))
-    {
-        return -OR_EINVAL;
-    }
-
-    // Check that address is within space
-    if (vaddr < space->start_addr || vaddr >= space->end_addr)
-    {
-        return -OR_EINVAL;
-    }
-
-    // Extraire les indices des tables de pages
     uint64_t pml4_idx = (vaddr >> 39) & 0x1FF;
     uint64_t pdpt_idx = (vaddr >> 30) & 0x1FF;
     uint64_t pd_idx = (vaddr >> 21) & 0x1FF;
-    uint64_t pt_idx = (vaddr >> 12) & 0x1FF;
 
     // Get PML4
     uint64_t *pml4 = (uint64_t *)space->pml4_phys;
@@ -220,7 +205,7 @@ int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t fla
         uint64_t pdpt_phys = pmm_alloc_page();
         if (!pdpt_phys)
         {
-            return -OR_ENOMEM;
+            return NULL;
         }
 
         // Initialize the new table
@@ -230,7 +215,9 @@ int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t fla
             pdpt[i] = 0;
         }
 
-        pml4[pml4_idx] = pdpt_phys | flags | 1; // Present + flags
+        // Upper levels allow everything: the permissions of a page are
+        // those of its own entry, whatever was mapped first next to it
+        pml4[pml4_idx] = pdpt_phys | PTE_USER | PTE_WRITE | PTE_PRESENT;
     }
 
     // Get PDPT
@@ -242,7 +229,7 @@ int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t fla
         uint64_t pd_phys = pmm_alloc_page();
         if (!pd_phys)
         {
-            return -OR_ENOMEM;
+            return NULL;
         }
 
         uint64_t *pd = (uint64_t *)pd_phys;
@@ -251,14 +238,96 @@ int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t fla
             pd[i] = 0;
         }
 
-        pdpt[pdpt_idx] = pd_phys | flags | 1;
+        pdpt[pdpt_idx] = pd_phys | PTE_USER | PTE_WRITE | PTE_PRESENT;
     }
 
-    // Get PD
     uint64_t *pd = (uint64_t *)(pdpt[pdpt_idx] & 0xFFFFFFFFFFFFF000ULL);
+    return &pd[pd_idx];
+}
+
+// Page directory entry covering `vaddr`, NULL when there is no page
+// directory there
+static uint64_t *pde_lookup(vm_space_t *space, uint64_t vaddr)
+{
+    uint64_t *table = (uint64_t *)space->pml4_phys;
+    for (int shift = 39; shift > 21; shift -= 9)
+    {
+        uint64_t entry = table[(vaddr >> shift) & 0x1FF];
+        if (!(entry & 1))
+        {
+            return NULL;
+        }
+        table = (uint64_t *)(entry & 0xFFFFFFFFFFFFF000ULL);
+    }
+    return &table[(vaddr >> 21) & 0x1FF];
+}
+
+// The 4KB page table entry a huge page directory entry stands for at `vaddr`
+static uint64_t huge_piece(uint64_t pde, uint64_t vaddr)
+{
+    uint64_t base = pde & 0xFFFFFFFFFFFFF000ULL & ~(HUGE_PAGE_SIZE - 1);
+    return (base + (ROUND_DOWN(vaddr, PAGE_SIZE) & (HUGE_PAGE_SIZE - 1))) | (pde & 0xFFF & ~PTE_HUGE);
+}
+
+// Replace the huge page at `pde` by a page table mapping the same frames
+// with the same flags. The frames were allocated one by one as far as the
+// PMM is concerned, so each small page can then be freed on its own
+static int split_pde(uint64_t *pde, uint64_t vaddr)
+{
+    uint64_t pt_phys = pmm_alloc_page();
+    if (!pt_phys)
+    {
+        return -OR_ENOMEM;
+    }
+
+    uint64_t *pt = (uint64_t *)pt_phys;
+    uint64_t block = ROUND_DOWN(vaddr, HUGE_PAGE_SIZE);
+    for (uint64_t i = 0; i < PT_ENTRIES; i++)
+    {
+        pt[i] = huge_piece(*pde, block + i * PAGE_SIZE);
+    }
+    *pde = pt_phys | PTE_USER | PTE_WRITE | PTE_PRESENT;
+    mmu_invalidate_page(block);
+
+    kdebug("vmm: split huge page at 0x%p", (void *)block);
+    return OR_OK;
+}
+
+// Map a virtual page to a physical page
+int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t flags)
+{
+    if (!vmm_initialized || !space)
+    {
+        return -OR_EINVAL;
+    }
+
+    // Check alignment
+    if (!IS_ALIGNED(vaddr, PAGE_SIZE) || !IS_ALIGNED(paddr, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+
+    // Check that address is within space
+    if (vaddr < space->start_addr || vaddr >= space->end_addr)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint64_t pt_idx = (vaddr >> 12) & 0x1FF;
+    uint64_t *pde = pde_create(space, vaddr);
+    if (!pde)
+    {
+        return -OR_ENOMEM;
+    }
+
+    // 4KB pages inside a huge page go to its split copy
+    if ((*pde & PTE_HUGE) && split_pde(pde, vaddr) != OR_OK)
+    {
+        return -OR_ENOMEM;
+    }
 
     // Allocate PT if necessary
-    if (!(pd[pd_idx] & 1))
+    if (!(*pde & 1))
     {
         uint64_t pt_phys = pmm_alloc_page();
         if (!pt_phys)
@@ -272,12 +341,12 @@ int vmm_map_page(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t fla
             pt[i] = 0;
         }
 
-        pd[pd_idx] = pt_phys | flags | 1;
+        *pde = pt_phys | PTE_USER | PTE_WRITE | PTE_PRESENT;
     }
 
     // Get PT and map the page
-    uint64_t *pt = (uint64_t *)(pd[pd_idx] & 0xFFFFFFFFFFFFF000ULL);
-    pt[pt_idx] = paddr | flags | 1; // Present + flags
+    uint64_t *pt = (uint64_t *)(*pde & 0xFFFFFFFFFFFFF000ULL);
+    pt[pt_idx] = paddr | (flags & ~PAGE_FLAG_HUGE) | 1; // Present + flags
 
     // Invalider la TLB pour cette page
     mmu_invalidate_page(vaddr);
@@ -329,6 +398,12 @@ int vmm_unmap_page(vm_space_t *space, uint64_t vaddr)
         return OR_OK;
     }
 
+    // Unmapping part of a huge page leaves the rest as small pages
+    if ((pd[pd_idx] & PTE_HUGE) && split_pde(&pd[pd_idx], vaddr) != OR_OK)
+    {
+        return -OR_ENOMEM;
+    }
+
     uint64_t *pt = (uint64_t *)(pd[pd_idx] & 0xFFFFFFFFFFFFF000ULL);
     if (!(pt[pt_idx] & 1))
     {
@@ -388,6 +463,11 @@ int vmm_protect_page(vm_space_t *space, uint64_t vaddr, uint64_t new_flags)
         return -OR_EINVAL;
     }
 
+    if ((pd[pd_idx] & PTE_HUGE) && split_pde(&pd[pd_idx], vaddr) != OR_OK)
+    {
+        return -OR_ENOMEM;
+    }
+
     uint64_t *pt = (uint64_t *)(pd[pd_idx] & 0xFFFFFFFFFFFFF000ULL);
     if (!(pt[pt_idx] & 1))
     {
@@ -399,7 +479,7 @@ int vmm_protect_page(vm_space_t *space, uint64_t vaddr, uint64_t new_flags)
     uint64_t old_flags = pt[pt_idx] & 0xFFF;
 
     // Apply new permissions
-    pt[pt_idx] = paddr | new_flags | 1;
+    pt[pt_idx] = paddr | (new_flags & ~PAGE_FLAG_HUGE) | 1;
 
     // Invalider la TLB
     mmu_invalidate_page(vaddr);
@@ -410,6 +490,171 @@ int vmm_protect_page(vm_space_t *space, uint64_t vaddr, uint64_t new_flags)
     return OR_OK;
 }
 
+// Physical address and flags of the page mapped at `vaddr` in `space`;
+// unlike the mmu_* helpers this works on spaces that are not loaded. A
+// huge page reports the 4KB page at `vaddr`
+bool vmm_query_page(vm_space_t *space, uint64_t vaddr, uint64_t *paddr, uint64_t *flags)
+{
+    if (!vmm_initialized || !space)
+    {
+        return false;
+    }
+
+    uint64_t *pde = pde_lookup(space, vaddr);
+    if (!pde || !(*pde & 1))
+    {
+        return false;
+    }
+    uint64_t entry;
+    if (*pde & PTE_HUGE)
+    {
+        entry = huge_piece(*pde, vaddr);
+    }
+    else
+    {
+        entry = ((uint64_t *)(*pde & 0xFFFFFFFFFFFFF000ULL))[(vaddr >> 12) & 0x1FF];
+        if (!(entry & 1))
+        {
+            return false;
+        }
+    }
+    if (paddr)
+    {
+        *paddr = entry & 0xFFFFFFFFFFFFF000ULL;
+    }
+    if (flags)
+    {
+        *flags = entry & 0xFFF;
+    }
+    return true;
+}
+
+int vmm_map_huge(vm_space_t *space, uint64_t vaddr, uint64_t paddr, uint64_t flags)
+{
+    if (!vmm_initialized || !space || !IS_ALIGNED(vaddr, HUGE_PAGE_SIZE) || !IS_ALIGNED(paddr, HUGE_PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+    if (vaddr < space->start_addr || vaddr + HUGE_PAGE_SIZE > space->end_addr)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint64_t *pde = pde_create(space, vaddr);
+    if (!pde)
+    {
+        return -OR_ENOMEM;
+    }
+    if (*pde & 1)
+    {
+        if (*pde & PTE_HUGE)
+        {
+            return -OR_EEXIST;
+        }
+        // A page table left empty by earlier unmaps gives way
+        uint64_t *pt = (uint64_t *)(*pde & 0xFFFFFFFFFFFFF000ULL);
+        for (int i = 0; i < PT_ENTRIES; i++)
+        {
+            if (pt[i] & 1)
+            {
+                return -OR_EEXIST;
+            }
+        }
+        *pde = 0;
+        pmm_free_page((uint64_t)pt);
+    }
+
+    *pde = paddr | (flags & 0xFFF) | PTE_HUGE | PTE_PRESENT;
+    mmu_invalidate_page(vaddr);
+
+    kdebug("vmm_map_huge: 0x%p -> 0x%p (flags=0x%p)", (void *)vaddr, (void *)paddr, (void *)flags);
+    return OR_OK;
+}
+
+int vmm_split_huge(vm_space_t *space, uint64_t vaddr)
+{
+    if (!vmm_initialized || !space)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint64_t *pde = pde_lookup(space, vaddr);
+    if (!pde || !(*pde & 1) || !(*pde & PTE_HUGE))
+    {
+        return OR_OK;
+    }
+    return split_pde(pde, vaddr);
+}
+
+// Call `visit` on every mapped user page of `space` until it returns
+// something other than OR_OK, which is then returned. Huge pages are
+// visited as their 4KB pieces: split first when `split` is set, so the
+// visitor may change entries, otherwise through read-only copies
+typedef int (*pte_visitor_t)(uint64_t vaddr, uint64_t *pte, void *context);
+
+static int walk_user_pages(vm_space_t *space, pte_visitor_t visit, void *context, bool split)
+{
+    uint64_t *pml4 = (uint64_t *)space->pml4_phys;
+    for (uint64_t pml4_idx = 0; pml4_idx < 256; pml4_idx++)
+    {
+        if (!(pml4[pml4_idx] & 1))
+            continue;
+        uint64_t *pdpt = (uint64_t *)(pml4[pml4_idx] & 0xFFFFFFFFFFFFF000ULL);
+
+        for (uint64_t pdpt_idx = 0; pdpt_idx < 512; pdpt_idx++)
+        {
+            if (!(pdpt[pdpt_idx] & 1))
+                continue;
+            uint64_t *pd = (uint64_t *)(pdpt[pdpt_idx] & 0xFFFFFFFFFFFFF000ULL);
+
+            for (uint64_t pd_idx = 0; pd_idx < 512; pd_idx++)
+            {
+                if (!(pd[pd_idx] & 1))
+                    continue;
+                uint64_t block = (pml4_idx << 39) | (pdpt_idx << 30) | (pd_idx << 21);
+                if (pd[pd_idx] & PTE_HUGE)
+                {
+                    if (split)
+                    {
+                        int result = split_pde(&pd[pd_idx], block);
+                        if (result != OR_OK)
+                        {
+                            return result;
+                        }
+                    }
+                    else
+                    {
+                        for (uint64_t pt_idx = 0; pt_idx < 512; pt_idx++)
+                        {
+                            uint64_t piece = huge_piece(pd[pd_idx], block | (pt_idx << 12));
+                            int result = visit(block | (pt_idx << 12), &piece, context);
+                            if (result != OR_OK)
+                            {
+                                return result;
+                            }
+                        }
+                        continue;
+                    }
+                }
+                uint64_t *pt = (uint64_t *)(pd[pd_idx] & 0xFFFFFFFFFFFFF000ULL);
+
+                for (uint64_t pt_idx = 0; pt_idx < 512; pt_idx++)
+                {
+                    if (!(pt[pt_idx] & 1))
+                        continue;
+                    uint64_t vaddr = block | (pt_idx << 12);
+                    int result = visit(vaddr, &pt[pt_idx], context);
+                    if (result != OR_OK)
+                    {
+                        return result;
+                    }
+                }
+            }
+        }
+    }
+    return OR_OK;
+}
+
 // Allocate and map virtual pages
 uint64_t vmm_alloc_pages(vm_space_t *space, size_t count, uint64_t flags)
 {
@@ -466,7 +711,7 @@ uint64_t vmm_alloc_pages(vm_space_t *space, size_t count, uint64_t flags)
                             space_free = false;
                             break;
                         }
-                        if (mmu_is_valid_addr(check_vaddr))
+                        if (mmu_is_valid_addr(check_vaddr) || aslr_is_guard(space, check_vaddr))
                         {
                             space_free = false;
                             break;
@@ -535,7 +780,7 @@ uint64_t vmm_alloc_pages(vm_space_t *space, size_t count, uint64_t flags)
         // Check if all pages in range are free
         for (size_t i = 0; i < count; i++)
         {
-            if (mmu_is_valid_addr(vaddr + i * PAGE_SIZE))
+            if (mmu_is_valid_addr(vaddr + i * PAGE_SIZE) || aslr_is_guard(space, vaddr + i * PAGE_SIZE))
             {
                 space_free = false;
                 break;
@@ -587,6 +832,103 @@ uint64_t vmm_alloc_pages(vm_space_t *space, size_t count, uint64_t flags)
     return 0;
 }
 
+// Allocate and map virtual pages at a fixed address
+int vmm_alloc_pages_at(vm_space_t *space, uint64_t vaddr, size_t count, uint64_t flags)
+{
+    if (!vmm_initialized || !space || count == 0 || !IS_ALIGNED(vaddr, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+
+    for (size_t i = 0; i < count; i++)
+    {
+        uint64_t page_vaddr = vaddr + i * PAGE_SIZE;
+        if (mmu_is_valid_addr(page_vaddr) || aslr_is_guard(space, page_vaddr))
+        {
+            return -OR_EBUSY;
+        }
+    }
+
+    for (size_t i = 0; i < count; i++)
+    {
+        uint64_t page_vaddr = vaddr + i * PAGE_SIZE;
+        uint64_t page_paddr = pmm_alloc_page();
+        if (!page_paddr || vmm_map_page(space, page_vaddr, page_paddr, flags) != OR_OK)
+        {
+            if (page_paddr)
+            {
+                pmm_free_page(page_paddr);
+            }
+            for (size_t j = 0; j < i; j++)
+            {
+                vmm_unmap_page(space, vaddr + j * PAGE_SIZE);
+            }
+            kerror("vmm_alloc_pages_at: failed to map page at 0x%p", (void *)page_vaddr);
+            return -OR_ENOMEM;
+        }
+    }
+
+    kdebug("vmm_alloc_pages_at: allocated %llu pages at 0x%p", (unsigned long long)count, (void *)vaddr);
+    return OR_OK;
+}
+
+// Map `count` physically contiguous frames from the DMA32 zone at
+// `vaddr`, for buffers handed to devices that only see 32-bit addresses.
+// Buffers of 2MB and more come in 2MB-aligned blocks, and every 2MB of
+// them at a 2MB-aligned `vaddr` is mapped as a huge page. The pages free
+// individually through vmm_free_pages
+int vmm_alloc_dma_at(vm_space_t *space, uint64_t vaddr, size_t count, uint64_t flags)
+{
+    if (!vmm_initialized || !space || count == 0 || !IS_ALIGNED(vaddr, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
+
+    for (size_t i = 0; i < count; i++)
+    {
+        uint64_t page_vaddr = vaddr + i * PAGE_SIZE;
+        if (mmu_is_valid_addr(page_vaddr) || aslr_is_guard(space, page_vaddr))
+        {
+            return -OR_EBUSY;
+        }
+    }
+
+    uint64_t paddr = pmm_alloc_frames(count, PMM_ALLOC_DMA32 | PMM_ALLOC_ZERO);
+    if (!paddr)
+    {
+        kerror("vmm_alloc_dma_at: no %llu contiguous DMA32 pages", (unsigned long long)count);
+        return -OR_ENOMEM;
+    }
+
+    size_t i = 0;
+    while (i < count)
+    {
+        uint64_t page_vaddr = vaddr + i * PAGE_SIZE;
+        uint64_t page_paddr = paddr + i * PAGE_SIZE;
+        if (count - i >= HUGE_PAGE_PAGES && IS_ALIGNED(page_vaddr, HUGE_PAGE_SIZE) &&
+            IS_ALIGNED(page_paddr, HUGE_PAGE_SIZE) && vmm_map_huge(space, page_vaddr, page_paddr, flags) == OR_OK)
+        {
+            i += HUGE_PAGE_PAGES;
+            continue;
+        }
+        if (vmm_map_page(space, page_vaddr, page_paddr, flags) != OR_OK)
+        {
+            for (size_t j = 0; j < i; j++)
+            {
+                vmm_unmap_page(space, vaddr + j * PAGE_SIZE);
+            }
+            pmm_free_frames(paddr, count);
+            kerror("vmm_alloc_dma_at: failed to map page at 0x%p", (void *)page_vaddr);
+            return -OR_ENOMEM;
+        }
+        i++;
+    }
+
+    kdebug("vmm_alloc_dma_at: %llu pages at 0x%p backed by 0x%p", (unsigned long long)count, (void *)vaddr,
+           (void *)paddr);
+    return OR_OK;
+}
+
 // Free virtual pages
 void vmm_free_pages(vm_space_t *space, uint64_t vaddr, size_t count)
 {
@@ -634,6 +976,14 @@ void vmm_destroy_space(vm_space_t *space)
 
     kdebug("vmm_destroy_space: destroying user space 0x%p", (void *)space);
 
+    // The guard pages of the space's stacks and buffers go with it
+    aslr_release_space(space);
+    ksm_release_space(space);
+
+    // Device and shared pages are unmapped here so the walk below only
+    // frees pages the space owns
+    vma_release_space(space);
+
     // Free all mapped pages in user space
     // Walk only the first 256 PML4 entries (user space)
     uint64_t *pml4 = (uint64_t *)space->pml4_phys;
@@ -657,6 +1007,12 @@ void vmm_destroy_space(vm_space_t *space)
                 if (!(pd[pd_idx] & 1))
                     continue;
 
+                if (pd[pd_idx] & PTE_HUGE)
+                {
+                    pmm_free_frames(pd[pd_idx] & 0xFFFFFFFFFFFFF000ULL & ~(HUGE_PAGE_SIZE - 1), HUGE_PAGE_PAGES);
+                    continue;
+                }
+
                 uint64_t *pt = (uint64_t *)(pd[pd_idx] & 0xFFFFFFFFFFFFF000ULL);
 
                 // Free all mapped physical pages
@@ -694,6 +1050,11 @@ vm_space_t *vmm_get_kernel_space(void)
     return vmm_initialized ? &kernel_space : NULL;
 }
 
+vma_tree_t *vmm_space_vmas(vm_space_t *space)
+{
+    return space ? &space->vmas : NULL;
+}
+
 // ========================================
 // ADVANCED TLB MANAGEMENT
 // ========================================
@@ -747,7 +1108,7 @@ static uint64_t vmm_find_free_range(vm_space_t *space, size_t count)
         // Detailed check for all pages in range
         for (size_t i = 0; i < count; i++)
         {
-            if (mmu_is_valid_addr(vaddr + i * PAGE_SIZE))
+            if (mmu_is_valid_addr(vaddr + i * PAGE_SIZE) || aslr_is_guard(space, vaddr + i * PAGE_SIZE))
             {
                 space_free = false;
                 break;
@@ -959,39 +1320,33 @@ int vmm_mark_cow(vm_space_t *space, uint64_t vaddr)
 
     kdebug("vmm_mark_cow: Marking COW for 0x%p", (void *)vaddr);
 
-    // Get physical address of the page
-    uint64_t paddr = mmu_virt_to_phys(vaddr);
-    if (paddr == 0)
+    uint64_t paddr;
+    uint64_t current_flags;
+    if (!vmm_query_page(space, vaddr, &paddr, &current_flags))
     {
         kerror("vmm_mark_cow: Page not mapped at 0x%p", (void *)vaddr);
         return -OR_EINVAL;
     }
 
-    // 1. Mark page as read-only by removing write permission
-    uint64_t current_flags = mmu_get_page_flags(vaddr); // Get current page flags from page table
-    if (current_flags == 0)
+    // 1. Record the extra mapping before the page becomes read-only
+    int result = pmm_page_share(paddr);
+    if (result != OR_OK)
     {
-        kerror("vmm_mark_cow: Failed to get page flags for 0x%p", (void *)vaddr);
-        return -OR_EINVAL;
+        return result;
     }
 
+    // 2. Mark page as read-only by removing write permission
     uint64_t new_flags = (current_flags & ~PAGE_FLAG_WRITE) | PAGE_FLAG_COW;
-
-    int result = vmm_protect_page(space, vaddr, new_flags);
+    result = vmm_protect_page(space, vaddr, new_flags);
     if (result != OR_OK)
     {
         kerror("vmm_mark_cow: Failed to protect page");
+        pmm_free_page(paddr);
         return result;
     }
 
-    // 2. Increment reference count for the physical page
-    vmm_inc_page_ref(paddr);
-
-    // 3. Flush TLB for this page
-    mmu_invalidate_page(vaddr);
-
-    kinfo("vmm_mark_cow: Successfully marked COW for 0x%p (paddr: 0x%p)",
-          (void *)vaddr, (void *)paddr);
+    kdebug("vmm_mark_cow: Successfully marked COW for 0x%p (paddr: 0x%p)",
+           (void *)vaddr, (void *)paddr);
 
     return OR_OK;
 }
@@ -1004,24 +1359,32 @@ int vmm_handle_cow_fault(vm_space_t *space, uint64_t vaddr)
         return -OR_EINVAL;
     }
 
+    vaddr = ROUND_DOWN(vaddr, PAGE_SIZE);
     kdebug("vmm_handle_cow_fault: Handling COW fault for 0x%p", (void *)vaddr);
 
-    // Get original physical address
-    uint64_t orig_paddr = mmu_virt_to_phys(vaddr);
-    if (orig_paddr == 0)
+    uint64_t orig_paddr;
+    uint64_t flags;
+    if (!vmm_query_page(space, vaddr, &orig_paddr, &flags))
     {
         kerror("vmm_handle_cow_fault: Page not mapped at 0x%p", (void *)vaddr);
         return -OR_EINVAL;
     }
 
-    // Check if this is actually a COW page
-    page_ref_t *page_ref = vmm_get_page_ref(orig_paddr);
-    if (!page_ref || atomic_load(&page_ref->ref_count) <= 1)
+    // Only pages shared by fork; a write the area forbids stays a fault
+    // even when the page is still marked
+    vma_t area;
+    if (!(flags & PAGE_FLAG_COW) || (vma_lookup(space, vaddr, &area) && !(area.prot & VM_FLAG_WRITE)))
+    {
+        return -OR_EPERM;
+    }
+
+    uint64_t new_flags = (flags & ~PAGE_FLAG_COW) | PAGE_FLAG_WRITE;
+
+    // The other mappings are gone: the page is ours again
+    if (pmm_page_mapcount(orig_paddr) <= 1)
     {
-        // Not a shared page, just restore write permission
         kdebug("vmm_handle_cow_fault: Not shared, restoring write permission");
-        uint64_t flags = PAGE_FLAG_PRESENT | PAGE_FLAG_WRITE | PAGE_FLAG_USER;
-        return vmm_protect_page(space, vaddr, flags);
+        return vmm_protect_page(space, vaddr, new_flags);
     }
 
     // 1. Allocate new physical page
@@ -1038,7 +1401,6 @@ int vmm_handle_cow_fault(vm_space_t *space, uint64_t vaddr)
     memcpy(new_page, orig_page, PAGE_SIZE);
 
     // 3. Update page table to point to new page with write permission
-    uint64_t new_flags = PAGE_FLAG_PRESENT | PAGE_FLAG_WRITE | PAGE_FLAG_USER;
     int result = vmm_map_page(space, vaddr, new_paddr, new_flags);
     if (result != OR_OK)
     {
@@ -1047,18 +1409,160 @@ int vmm_handle_cow_fault(vm_space_t *space, uint64_t vaddr)
         return result;
     }
 
-    // 4. Decrement reference count for original page
-    vmm_dec_page_ref(orig_paddr);
+    // 4. Drop this space's share of the original page
+    pmm_free_page(orig_paddr);
 
-    // 5. Flush TLB for this page
-    mmu_invalidate_page(vaddr);
+    vma_tree_t *vmas = vmm_space_vmas(space);
+    spinlock_lock(&vmas->lock);
+    vmas->stats.copies++;
+    spinlock_unlock(&vmas->lock);
 
-    kinfo("vmm_handle_cow_fault: COW fault resolved for 0x%p (new paddr: 0x%p)",
-          (void *)vaddr, (void *)new_paddr);
+    kdebug("vmm_handle_cow_fault: COW fault resolved for 0x%p (new paddr: 0x%p)",
+           (void *)vaddr, (void *)new_paddr);
 
     return OR_OK;
 }
 
+// ========================================
+// FORK AND MEMORY ACCOUNTING
+// ========================================
+
+typedef struct fork_context
+{
+    vm_space_t *parent;
+    vm_space_t *child;
+} fork_context_t;
+
+static int fork_page(uint64_t vaddr, uint64_t *pte, void *context)
+{
+    fork_context_t *fork = (fork_context_t *)context;
+    uint64_t paddr = *pte & 0xFFFFFFFFFFFFF000ULL;
+    uint64_t flags = *pte & 0xFFF;
+
+    vma_t area;
+    bool in_area = vma_lookup(fork->parent, vaddr, &area);
+    if (in_area && (area.flags & VMA_DMA))
+    {
+        return OR_OK; // DMA buffers are not inherited
+    }
+
+    // Device and shared memory objects are mapped as they are
+    if (in_area && (area.type == VMA_DEVICE || area.type == VMA_SHARED))
+    {
+        return vmm_map_page(fork->child, vaddr, paddr, flags);
+    }
+
+    if (pmm_page_share(paddr) != OR_OK)
+    {
+        // Too many mappings already: the child gets its own copy
+        uint64_t copy = pmm_alloc_page();
+        if (!copy)
+        {
+            return -OR_ENOMEM;
+        }
+        memcpy((void *)copy, (void *)paddr, PAGE_SIZE);
+        int result = vmm_map_page(fork->child, vaddr, copy, flags);
+        if (result != OR_OK)
+        {
+            pmm_free_page(copy);
+        }
+        return result;
+    }
+
+    // Writable pages become copy-on-write on both sides
+    if (flags & (PAGE_FLAG_WRITE | PAGE_FLAG_COW))
+    {
+        flags = (flags & ~PAGE_FLAG_WRITE) | PAGE_FLAG_COW;
+        *pte = paddr | flags;
+    }
+
+    int result = vmm_map_page(fork->child, vaddr, paddr, flags);
+    if (result != OR_OK)
+    {
+        pmm_free_page(paddr); // The share taken above
+    }
+    return result;
+}
+
+vm_space_t *vmm_fork_space(vm_space_t *parent)
+{
+    if (!vmm_initialized || !parent || parent->is_kernel)
+    {
+        return NULL;
+    }
+
+    vm_space_t *child = vmm_create_space(false);
+    if (!child)
+    {
+        return NULL;
+    }
+
+    int result = vma_clone(parent, child);
+    if (result == OR_OK)
+    {
+        fork_context_t context = {parent, child};
+        // Copy-on-write works on small pages
+        result = walk_user_pages(parent, fork_page, &context, true);
+    }
+    if (result == OR_OK)
+    {
+        ksm_fork_space(parent, child);
+    }
+
+    // Parent pages lost their write permission
+    mmu_flush_tlb();
+
+    if (result != OR_OK)
+    {
+        kerror("vmm_fork_space: cannot copy address space (error %d)", result);
+        vmm_destroy_space(child);
+        return NULL;
+    }
+
+    kdebug("vmm_fork_space: forked space 0x%p into 0x%p", (void *)parent, (void *)child);
+    return child;
+}
+
+static int count_page(uint64_t vaddr, uint64_t *pte, void *context)
+{
+    (void)vaddr;
+    vm_usage_t *usage = (vm_usage_t *)context;
+
+    // Device memory and other frames the PMM does not hand out
+    uint32_t mappings = pmm_page_mapcount(*pte & 0xFFFFFFFFFFFFF000ULL);
+    if (mappings == 0)
+    {
+        return OR_OK;
+    }
+
+    usage->rss += PAGE_SIZE;
+    usage->pss += PAGE_SIZE / mappings;
+    if (mappings == 1)
+    {
+        usage->uss += PAGE_SIZE;
+    }
+    else
+    {
+        usage->shared += PAGE_SIZE;
+    }
+    return OR_OK;
+}
+
+int vmm_get_usage(vm_space_t *space, vm_usage_t *usage)
+{
+    if (!vmm_initialized || !space || !usage)
+    {
+        return -OR_EINVAL;
+    }
+
+    memset(usage, 0, sizeof(*usage));
+    if (space->is_kernel)
+    {
+        return OR_OK;
+    }
+    return walk_user_pages(space, count_page, usage, false);
+}
+
 // ========================================
 // MEMORY PREFAULTING AND OPTIMIZATION
 // ========================================
@@ -1080,6 +1584,17 @@ int vmm_prefault_range(vm_space_t *space, uint64_t vaddr, size_t count)
         // Only prefault if page is not already mapped
         if (!mmu_is_valid_addr(page_vaddr))
         {
+            // Pages of an area are populated the way a fault would
+            if (vma_lookup(space, page_vaddr, NULL))
+            {
+                if (vma_populate(space, page_vaddr, PAGE_SIZE) != OR_OK)
+                {
+                    return -OR_ENOMEM;
+                }
+                prefaulted++;
+                continue;
+            }
+
             uint64_t page_paddr = pmm_alloc_page();
             if (page_paddr)
             {
@@ -1102,78 +1617,85 @@ int vmm_prefault_range(vm_space_t *space, uint64_t vaddr, size_t count)
     return OR_OK;
 }
 
-// ========================================
-// COW PAGE REFERENCE MANAGEMENT
-// ========================================
-
-// Get page reference structure for a physical address
-page_ref_t *vmm_get_page_ref(uint64_t paddr)
+// Pin or unpin a range of pages. Locked pages are populated immediately and
+// carry PAGE_FLAG_LOCKED so reclaim and dump paths leave them alone; this is
+// what the keyring server uses to hold key material.
+int vmm_lock_range(vm_space_t *space, uint64_t vaddr, size_t count, bool lock)
 {
-    uint64_t page_index = paddr / PAGE_SIZE;
-    if (page_index >= MAX_PAGE_REFS)
+    if (!space || !vmm_initialized || count == 0)
     {
-        kerror("vmm_get_page_ref: Page index %llu exceeds maximum",
-               (unsigned long long)page_index);
-        return NULL;
+        return -OR_EINVAL;
     }
 
-    page_ref_t *ref = &page_refs[page_index];
+    if (!IS_ALIGNED(vaddr, PAGE_SIZE))
+    {
+        return -OR_EINVAL;
+    }
 
-    // Initialize if not already done
-    if (ref->physical_addr == 0)
+    if (lock)
     {
-        spinlock_lock(&page_refs_lock);
-        if (ref->physical_addr == 0)
-        { // Double-check after acquiring lock
-            ref->physical_addr = paddr;
-            atomic_store(&ref->ref_count, 1);
-            spinlock_init(&ref->lock);
+        int result = vmm_prefault_range(space, vaddr, count);
+        if (result != OR_OK)
+        {
+            return result;
+        }
+    }
+
+    for (size_t i = 0; i < count; i++)
+    {
+        uint64_t page_vaddr = vaddr + i * PAGE_SIZE;
+
+        // Huge pages covered whole keep their single entry
+        uint64_t *pde = NULL;
+        if (IS_ALIGNED(page_vaddr, HUGE_PAGE_SIZE) && count - i >= HUGE_PAGE_PAGES)
+        {
+            pde = pde_lookup(space, page_vaddr);
+        }
+        if (pde && (*pde & 1) && (*pde & PTE_HUGE))
+        {
+            *pde = lock ? (*pde | PAGE_FLAG_LOCKED) : (*pde & ~(uint64_t)PAGE_FLAG_LOCKED);
+            mmu_invalidate_page(page_vaddr);
+            i += HUGE_PAGE_PAGES - 1;
+            continue;
+        }
+
+        uint64_t flags = mmu_get_page_flags(page_vaddr);
+
+        if (!(flags & PAGE_FLAG_PRESENT))
+        {
+            return -OR_EFAULT;
+        }
+
+        flags = lock ? (flags | PAGE_FLAG_LOCKED) : (flags & ~PAGE_FLAG_LOCKED);
+
+        int result = vmm_protect_page(space, page_vaddr, flags);
+        if (result != OR_OK)
+        {
+            return result;
         }
-        spinlock_unlock(&page_refs_lock);
     }
 
-    return ref;
+    kdebug("vmm_lock_range: %s %llu pages at 0x%p", lock ? "locked" : "unlocked",
+           (unsigned long long)count, (void *)vaddr);
+
+    return OR_OK;
 }
 
-// Increment page reference count
+// ========================================
+// COW PAGE REFERENCE MANAGEMENT
+// ========================================
+
+// Record one more mapping of a physical page
 void vmm_inc_page_ref(uint64_t paddr)
 {
-    page_ref_t *ref = vmm_get_page_ref(paddr);
-    if (!ref)
+    if (pmm_page_share(paddr) != OR_OK)
     {
-        kerror("vmm_inc_page_ref: Failed to get page reference for 0x%p", (void *)paddr);
-        return;
+        kerror("vmm_inc_page_ref: cannot share page 0x%p", (void *)paddr);
     }
-
-    uint32_t old_count = atomic_fetch_add(&ref->ref_count, 1);
-    kdebug("vmm_inc_page_ref: Page 0x%p ref count: %u -> %u",
-           (void *)paddr, old_count, old_count + 1);
 }
 
-// Decrement page reference count and free if needed
+// Drop a mapping of a physical page, freeing it with the last one
 void vmm_dec_page_ref(uint64_t paddr)
 {
-    page_ref_t *ref = vmm_get_page_ref(paddr);
-    if (!ref)
-    {
-        kerror("vmm_dec_page_ref: Failed to get page reference for 0x%p", (void *)paddr);
-        return;
-    }
-
-    uint32_t old_count = atomic_fetch_sub(&ref->ref_count, 1);
-    kdebug("vmm_dec_page_ref: Page 0x%p ref count: %u -> %u",
-           (void *)paddr, old_count, old_count - 1);
-
-    // If reference count reaches zero, free the physical page
-    if (old_count == 1)
-    {
-        kinfo("vmm_dec_page_ref: Freeing unreferenced page 0x%p", (void *)paddr);
-        pmm_free_page(paddr);
-
-        // Reset the reference structure
-        spinlock_lock(&ref->lock);
-        ref->physical_addr = 0;
-        atomic_store(&ref->ref_count, 0);
-        spinlock_unlock(&ref->lock);
-    }
+    pmm_free_page(paddr);
 }
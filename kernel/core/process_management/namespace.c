@@ -0,0 +1,590 @@
+/*
+ * Orion Operating System - Namespaces
+ *
+ * Membership is kept aside from the process structures, the way OOM
+ * groups are: a process without an entry is in the initial namespaces
+ * and numbered by its global PID, so the common case costs nothing. An
+ * entry holds the process's four namespaces and its number at each
+ * depth of its PID namespace chain, the global PID first. Namespaces are
+ * reference counted by their members and freed with the last one.
+ *
+ * A time namespace keeps the offsets set on it and their sum with those
+ * of the namespaces above it, which is what clock reads add. The sum
+ * stays valid because offsets are fixed before a namespace can get
+ * another member or a namespace below it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/scheduler.h>
+#include <orion/wallclock.h>
+#include "namespace.h"
+
+#define SIGKILL 9
+
+// Mount and network namespaces
+typedef struct ns_entry
+{
+    bool used;
+    uint32_t parent;
+    uint32_t members;
+} ns_entry_t;
+
+typedef struct ns_pid_namespace
+{
+    bool used;
+    bool dying;     // Its first process exited, nobody may join
+    uint32_t parent;
+    uint32_t depth;
+    uint32_t members;
+    uint64_t next_pid;
+    uint64_t init_pid; // Global PID of its PID 1
+} ns_pid_namespace_t;
+
+typedef struct ns_time_namespace
+{
+    bool used;
+    bool fixed; // Offsets may no longer change
+    uint32_t parent;
+    uint32_t members;
+    ns_clock_offsets_t offsets; // From the parent's clocks
+    ns_clock_offsets_t totals;  // From the kernel's clocks
+} ns_time_namespace_t;
+
+// Processes outside the initial namespaces; pid 0 marks a free slot
+typedef struct ns_member
+{
+    uint64_t pid;
+    uint32_t mount;
+    uint32_t net;
+    uint32_t pid_ns;
+    uint32_t time;
+    uint64_t numbers[NS_PID_MAX_DEPTH + 1]; // PID at each depth, [0] the global one
+} ns_member_t;
+
+static ns_entry_t g_mount_ns[NS_MAX_NAMESPACES];
+static ns_entry_t g_net_ns[NS_MAX_NAMESPACES];
+static ns_pid_namespace_t g_pid_ns[NS_MAX_NAMESPACES];
+static ns_time_namespace_t g_time_ns[NS_MAX_NAMESPACES];
+static ns_member_t g_members[NS_MAX_MEMBERS];
+static spinlock_t g_ns_lock = SPINLOCK_INIT;
+
+// Processes killed with the first process of their PID namespace
+static uint64_t g_doomed[NS_MAX_MEMBERS];
+
+// ========================================
+// TABLES
+// ========================================
+
+// Caller holds g_ns_lock
+static int member_slot(uint64_t pid)
+{
+    for (int i = 0; i < NS_MAX_MEMBERS; i++)
+    {
+        if (g_members[i].pid == pid)
+        {
+            return i;
+        }
+    }
+    return -1;
+}
+
+// Membership of `pid`, the initial namespaces without an entry. Caller
+// holds g_ns_lock
+static ns_member_t member_of(uint64_t pid)
+{
+    int slot = pid ? member_slot(pid) : -1;
+    if (slot >= 0)
+    {
+        return g_members[slot];
+    }
+    ns_member_t member;
+    memset(&member, 0, sizeof(member));
+    member.pid = pid;
+    member.numbers[0] = pid;
+    return member;
+}
+
+static bool member_initial(const ns_member_t *member)
+{
+    return member->mount == NS_INITIAL && member->net == NS_INITIAL && member->pid_ns == NS_INITIAL &&
+           member->time == NS_INITIAL;
+}
+
+// Caller holds g_ns_lock
+static int entry_alloc(ns_entry_t *table, uint32_t parent)
+{
+    for (uint32_t i = 1; i < NS_MAX_NAMESPACES; i++)
+    {
+        if (!table[i].used)
+        {
+            table[i].used = true;
+            table[i].parent = parent;
+            table[i].members = 0;
+            return (int)i;
+        }
+    }
+    return -OR_ENOSPC;
+}
+
+// Caller holds g_ns_lock
+static void entry_put(ns_entry_t *table, uint32_t id)
+{
+    if (id != NS_INITIAL && table[id].members > 0 && --table[id].members == 0)
+    {
+        table[id].used = false;
+    }
+}
+
+// Namespace at `depth` in the chain of `ns`. Caller holds g_ns_lock
+static uint32_t pid_ns_at(uint32_t ns, uint32_t depth)
+{
+    while (g_pid_ns[ns].depth > depth)
+    {
+        ns = g_pid_ns[ns].parent;
+    }
+    return ns;
+}
+
+// Caller holds g_ns_lock
+static void pid_ns_put(uint32_t id)
+{
+    if (id != NS_INITIAL && g_pid_ns[id].members > 0 && --g_pid_ns[id].members == 0)
+    {
+        g_pid_ns[id].used = false;
+    }
+}
+
+// Caller holds g_ns_lock
+static void time_ns_put(uint32_t id)
+{
+    if (id != NS_INITIAL && g_time_ns[id].members > 0 && --g_time_ns[id].members == 0)
+    {
+        g_time_ns[id].used = false;
+    }
+}
+
+// Caller holds g_ns_lock
+static void member_join(const ns_member_t *member)
+{
+    if (member->mount != NS_INITIAL)
+    {
+        g_mount_ns[member->mount].members++;
+    }
+    if (member->net != NS_INITIAL)
+    {
+        g_net_ns[member->net].members++;
+    }
+    if (member->pid_ns != NS_INITIAL)
+    {
+        g_pid_ns[member->pid_ns].members++;
+    }
+    if (member->time != NS_INITIAL)
+    {
+        g_time_ns[member->time].members++;
+    }
+}
+
+// Caller holds g_ns_lock
+static void member_leave(const ns_member_t *member)
+{
+    entry_put(g_mount_ns, member->mount);
+    entry_put(g_net_ns, member->net);
+    pid_ns_put(member->pid_ns);
+    time_ns_put(member->time);
+}
+
+// Store `member`, in the slot of its process if it has one. Caller holds
+// g_ns_lock
+static int member_store(const ns_member_t *member)
+{
+    int slot = member_slot(member->pid);
+    if (slot < 0)
+    {
+        slot = member_slot(0);
+    }
+    if (slot < 0)
+    {
+        return -OR_ENOSPC;
+    }
+    g_members[slot] = *member;
+    return OR_OK;
+}
+
+// ========================================
+// PUBLIC API
+// ========================================
+
+void ns_init(void)
+{
+    spinlock_init(&g_ns_lock);
+    memset(g_mount_ns, 0, sizeof(g_mount_ns));
+    memset(g_net_ns, 0, sizeof(g_net_ns));
+    memset(g_pid_ns, 0, sizeof(g_pid_ns));
+    memset(g_time_ns, 0, sizeof(g_time_ns));
+    memset(g_members, 0, sizeof(g_members));
+    g_mount_ns[NS_INITIAL].used = true;
+    g_net_ns[NS_INITIAL].used = true;
+    g_pid_ns[NS_INITIAL].used = true;
+    g_time_ns[NS_INITIAL].used = true;
+    g_time_ns[NS_INITIAL].fixed = true;
+    kinfo("Namespaces: %d of each kind, PID namespaces %d deep", NS_MAX_NAMESPACES, NS_PID_MAX_DEPTH);
+}
+
+int ns_unshare(uint64_t pid, uint32_t flags)
+{
+    if (!pid || !flags || (flags & ~NS_ALL))
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_ns_lock);
+    ns_member_t old = member_of(pid);
+    ns_member_t member = old;
+    int mount = -1, net = -1, pid_ns = -1, time = -1;
+    int result = OR_OK;
+
+    if (flags & NS_PID)
+    {
+        uint32_t depth = g_pid_ns[old.pid_ns].depth + 1;
+        pid_ns = -OR_EINVAL;
+        if (depth <= NS_PID_MAX_DEPTH)
+        {
+            pid_ns = -OR_ENOSPC;
+            for (uint32_t i = 1; i < NS_MAX_NAMESPACES; i++)
+            {
+                if (!g_pid_ns[i].used)
+                {
+                    memset(&g_pid_ns[i], 0, sizeof(g_pid_ns[i]));
+                    g_pid_ns[i].used = true;
+                    g_pid_ns[i].parent = old.pid_ns;
+                    g_pid_ns[i].depth = depth;
+                    g_pid_ns[i].next_pid = 2;
+                    g_pid_ns[i].init_pid = pid;
+                    pid_ns = (int)i;
+                    break;
+                }
+            }
+        }
+        if (pid_ns < 0)
+        {
+            result = pid_ns;
+        }
+        else
+        {
+            member.pid_ns = (uint32_t)pid_ns;
+            member.numbers[depth] = 1;
+        }
+    }
+    if (result == OR_OK && (flags & NS_MOUNT))
+    {
+        mount = entry_alloc(g_mount_ns, old.mount);
+        result = mount < 0 ? mount : OR_OK;
+        member.mount = mount < 0 ? old.mount : (uint32_t)mount;
+    }
+    if (result == OR_OK && (flags & NS_NET))
+    {
+        net = entry_alloc(g_net_ns, old.net);
+        result = net < 0 ? net : OR_OK;
+        member.net = net < 0 ? old.net : (uint32_t)net;
+    }
+    if (result == OR_OK && (flags & NS_TIME))
+    {
+        // Clocks start out as the parent's, until offsets are set
+        time = -OR_ENOSPC;
+        for (uint32_t i = 1; i < NS_MAX_NAMESPACES; i++)
+        {
+            if (!g_time_ns[i].used)
+            {
+                memset(&g_time_ns[i], 0, sizeof(g_time_ns[i]));
+                g_time_ns[i].used = true;
+                g_time_ns[i].parent = old.time;
+                g_time_ns[i].totals = g_time_ns[old.time].totals;
+                time = (int)i;
+                break;
+            }
+        }
+        result = time < 0 ? time : OR_OK;
+        member.time = time < 0 ? old.time : (uint32_t)time;
+    }
+    if (result == OR_OK)
+    {
+        result = member_store(&member);
+    }
+
+    if (result != OR_OK)
+    {
+        // Nothing joined the namespaces allocated so far
+        if (pid_ns > 0)
+        {
+            g_pid_ns[pid_ns].used = false;
+        }
+        if (mount > 0)
+        {
+            g_mount_ns[mount].used = false;
+        }
+        if (net > 0)
+        {
+            g_net_ns[net].used = false;
+        }
+        if (time > 0)
+        {
+            g_time_ns[time].used = false;
+        }
+        spinlock_unlock(&g_ns_lock);
+        return result;
+    }
+
+    // Its totals went into the new namespace
+    if (time > 0)
+    {
+        g_time_ns[old.time].fixed = true;
+    }
+    member_join(&member);
+    member_leave(&old);
+    spinlock_unlock(&g_ns_lock);
+
+    kinfo("Namespaces: PID %llu moved to mount %u, net %u, pid %u, time %u", (unsigned long long)pid,
+          member.mount, member.net, member.pid_ns, member.time);
+    return OR_OK;
+}
+
+int ns_set_clock_offsets(uint64_t pid, const ns_clock_offsets_t *offsets)
+{
+    if (!offsets)
+    {
+        return -OR_EINVAL;
+    }
+
+    int64_t monotonic = (int64_t)wallclock_monotonic_ns();
+    int64_t boottime = (int64_t)wallclock_boottime_ns();
+
+    spinlock_lock(&g_ns_lock);
+    ns_member_t member = member_of(pid);
+    ns_time_namespace_t *time = &g_time_ns[member.time];
+    if (member.time == NS_INITIAL)
+    {
+        spinlock_unlock(&g_ns_lock);
+        return -OR_EINVAL;
+    }
+    if (time->fixed)
+    {
+        spinlock_unlock(&g_ns_lock);
+        return -OR_EBUSY;
+    }
+
+    const ns_clock_offsets_t *above = &g_time_ns[time->parent].totals;
+    ns_clock_offsets_t totals = {
+        .monotonic = above->monotonic + offsets->monotonic,
+        .boottime = above->boottime + offsets->boottime,
+    };
+    if (monotonic + totals.monotonic < 0 || boottime + totals.boottime < 0)
+    {
+        spinlock_unlock(&g_ns_lock);
+        return -OR_EINVAL;
+    }
+    time->offsets = *offsets;
+    time->totals = totals;
+    spinlock_unlock(&g_ns_lock);
+
+    kinfo("Namespaces: time %u offset by %lld ns monotonic, %lld ns boot time", member.time,
+          (long long)offsets->monotonic, (long long)offsets->boottime);
+    return OR_OK;
+}
+
+void ns_clock_offsets(uint64_t pid, ns_clock_offsets_t *offsets)
+{
+    spinlock_lock(&g_ns_lock);
+    *offsets = g_time_ns[member_of(pid).time].totals;
+    spinlock_unlock(&g_ns_lock);
+}
+
+int ns_process_fork(uint64_t parent, uint64_t child)
+{
+    spinlock_lock(&g_ns_lock);
+    ns_member_t member = member_of(parent);
+    if (member_initial(&member))
+    {
+        spinlock_unlock(&g_ns_lock);
+        return OR_OK;
+    }
+    if (g_pid_ns[member.pid_ns].dying)
+    {
+        spinlock_unlock(&g_ns_lock);
+        return -OR_EINVAL;
+    }
+
+    member.pid = child;
+    member.numbers[0] = child;
+    uint32_t depth = g_pid_ns[member.pid_ns].depth;
+    for (uint32_t level = 1; level <= depth; level++)
+    {
+        member.numbers[level] = g_pid_ns[pid_ns_at(member.pid_ns, level)].next_pid;
+    }
+    int result = member_store(&member);
+    if (result == OR_OK)
+    {
+        // The child runs on the offsets of its parent's namespace
+        g_time_ns[member.time].fixed = true;
+
+        // Numbers are only taken once the child is sure to exist
+        for (uint32_t level = 1; level <= depth; level++)
+        {
+            g_pid_ns[pid_ns_at(member.pid_ns, level)].next_pid++;
+        }
+        member_join(&member);
+    }
+    spinlock_unlock(&g_ns_lock);
+    return result;
+}
+
+void ns_process_exit(uint64_t pid)
+{
+    size_t doomed = 0;
+
+    spinlock_lock(&g_ns_lock);
+    int slot = pid ? member_slot(pid) : -1;
+    if (slot < 0)
+    {
+        spinlock_unlock(&g_ns_lock);
+        return;
+    }
+    ns_member_t member = g_members[slot];
+    g_members[slot].pid = 0;
+
+    // The namespace ends with its first process, nested ones included
+    ns_pid_namespace_t *pid_ns = &g_pid_ns[member.pid_ns];
+    if (member.pid_ns != NS_INITIAL && pid_ns->init_pid == pid)
+    {
+        pid_ns->dying = true;
+        for (int i = 0; i < NS_MAX_MEMBERS; i++)
+        {
+            const ns_member_t *other = &g_members[i];
+            if (other->pid && g_pid_ns[other->pid_ns].depth >= pid_ns->depth &&
+                pid_ns_at(other->pid_ns, pid_ns->depth) == member.pid_ns)
+            {
+                g_doomed[doomed++] = other->pid;
+            }
+        }
+    }
+    member_leave(&member);
+    spinlock_unlock(&g_ns_lock);
+
+    for (size_t i = 0; i < doomed; i++)
+    {
+        process_t *process = scheduler_find_process(g_doomed[i]);
+        if (process)
+        {
+            signal_send(process, SIGKILL);
+        }
+    }
+}
+
+int ns_get_info(uint64_t pid, ns_info_t *info)
+{
+    if (!info)
+    {
+        return -OR_EINVAL;
+    }
+    if (!scheduler_find_process(pid))
+    {
+        return -OR_ENOENT;
+    }
+
+    spinlock_lock(&g_ns_lock);
+    ns_member_t member = member_of(pid);
+    memset(info, 0, sizeof(*info));
+    info->mount = member.mount;
+    info->net = member.net;
+    info->pid_ns = member.pid_ns;
+    info->depth = g_pid_ns[member.pid_ns].depth;
+    info->mount_parent = g_mount_ns[member.mount].parent;
+    info->net_parent = g_net_ns[member.net].parent;
+    info->local_pid = member.numbers[info->depth];
+    info->time = member.time;
+    info->time_parent = g_time_ns[member.time].parent;
+    info->clocks = g_time_ns[member.time].offsets;
+    spinlock_unlock(&g_ns_lock);
+    return OR_OK;
+}
+
+uint64_t ns_pid_to_viewer(uint64_t viewer, uint64_t pid)
+{
+    spinlock_lock(&g_ns_lock);
+    ns_member_t seen_by = member_of(viewer);
+    ns_member_t target = member_of(pid);
+    uint32_t depth = g_pid_ns[seen_by.pid_ns].depth;
+    uint64_t number = 0;
+    if (g_pid_ns[target.pid_ns].depth >= depth && pid_ns_at(target.pid_ns, depth) == seen_by.pid_ns)
+    {
+        number = target.numbers[depth];
+    }
+    spinlock_unlock(&g_ns_lock);
+    return number;
+}
+
+uint64_t ns_pid_from_viewer(uint64_t viewer, uint64_t local)
+{
+    spinlock_lock(&g_ns_lock);
+    ns_member_t seen_by = member_of(viewer);
+    uint32_t depth = g_pid_ns[seen_by.pid_ns].depth;
+    uint64_t pid = depth == 0 ? local : 0;
+    for (int i = 0; depth > 0 && local && i < NS_MAX_MEMBERS; i++)
+    {
+        const ns_member_t *member = &g_members[i];
+        if (member->pid && g_pid_ns[member->pid_ns].depth >= depth &&
+            pid_ns_at(member->pid_ns, depth) == seen_by.pid_ns && member->numbers[depth] == local)
+        {
+            pid = member->pid;
+            break;
+        }
+    }
+    spinlock_unlock(&g_ns_lock);
+    return pid;
+}
+
+bool ns_in_child_pid_ns(uint64_t pid)
+{
+    spinlock_lock(&g_ns_lock);
+    bool nested = member_of(pid).pid_ns != NS_INITIAL;
+    spinlock_unlock(&g_ns_lock);
+    return nested;
+}
+
+bool ns_in_use(uint32_t kind, uint32_t id)
+{
+    if (id == NS_INITIAL)
+    {
+        return true;
+    }
+    if (id >= NS_MAX_NAMESPACES)
+    {
+        return false;
+    }
+
+    spinlock_lock(&g_ns_lock);
+    bool used = false;
+    switch (kind)
+    {
+    case NS_MOUNT:
+        used = g_mount_ns[id].used;
+        break;
+    case NS_NET:
+        used = g_net_ns[id].used;
+        break;
+    case NS_PID:
+        used = g_pid_ns[id].used;
+        break;
+    case NS_TIME:
+        used = g_time_ns[id].used;
+        break;
+    }
+    spinlock_unlock(&g_ns_lock);
+    return used;
+}
@@ -0,0 +1,139 @@
+/*
+ * Orion Operating System - Namespaces
+ *
+ * Lightweight isolation for process trees. A process belongs to one
+ * mount, one network, one PID and one time namespace; children inherit
+ * all four.
+ * The kernel only tracks membership and PID numbering: the mount table
+ * of a mount namespace lives in the fs server and the interfaces of a
+ * network namespace in the net server, both of which look the sender of
+ * a request up with NS_CTL_INFO.
+ *
+ * PID namespaces nest. A process has a number in its own namespace and in
+ * every one above it, and only sees processes of its namespace and those
+ * below; getpid, proc_info, wait and signal use the caller's numbering.
+ * IPC senders stay identified by their global PID, as servers run in the
+ * initial namespaces. When the first process of a PID namespace exits,
+ * the rest of the namespace is killed.
+ *
+ * Time namespaces offset the monotonic and boot-time clocks of their
+ * members from those of the parent namespace, so that a workload
+ * restored from a checkpoint or migrated from another machine carries on
+ * from the time it had: the tool restoring it reads the clocks the
+ * workload saw, unshares a time namespace for the suspended process it
+ * restores into and sets the offsets before resuming it. Offsets are
+ * fixed once anything else joins the namespace or a namespace is made
+ * below it. The wall clock and sleeps, which are relative, are the same
+ * in every namespace.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NAMESPACE_H
+#define ORION_NAMESPACE_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define NS_MAX_NAMESPACES 64 // Of each kind, the initial one included
+#define NS_MAX_MEMBERS 256   // Processes outside the initial namespaces
+#define NS_PID_MAX_DEPTH 4   // PID namespaces nested below the initial one
+
+// Namespace kinds, also the NS_CTL_UNSHARE flags
+#define NS_MOUNT (1 << 0)
+#define NS_NET (1 << 1)
+#define NS_PID (1 << 2)
+#define NS_TIME (1 << 3)
+#define NS_ALL (NS_MOUNT | NS_NET | NS_PID | NS_TIME)
+
+// Id of the namespaces the system starts in
+#define NS_INITIAL 0
+
+// SYS_NS_CTL operations
+#define NS_CTL_UNSHARE 1 // pid, flags: new namespaces for a child not started yet
+#define NS_CTL_INFO 2    // pid (0 for the caller), ns_info_t *
+#define NS_CTL_CLOCKS 3  // pid, ns_clock_offsets_t *: offsets of its time namespace
+
+    // Clock offsets of a time namespace, from the clocks of its parent
+    typedef struct ns_clock_offsets
+    {
+        int64_t monotonic; // Nanoseconds added to CLOCK_ID_MONOTONIC
+        int64_t boottime;  // Nanoseconds added to CLOCK_ID_BOOTTIME
+    } ns_clock_offsets_t;
+
+    typedef struct ns_info
+    {
+        uint32_t mount;        // Namespace ids, NS_INITIAL for the initial ones
+        uint32_t net;
+        uint32_t pid_ns;
+        uint32_t depth;        // Nesting of pid_ns, 0 for the initial one
+        uint32_t mount_parent; // Mount namespace the mount table was copied from
+        uint32_t net_parent;   // Network namespace the interfaces are bridged to
+        uint64_t local_pid;    // PID in its own PID namespace
+        uint32_t time;
+        uint32_t time_parent;
+        ns_clock_offsets_t clocks; // Offsets of `time` from `time_parent`
+    } ns_info_t;
+
+    void ns_init(void);
+
+    /**
+     * Move a process into new namespaces of the kinds in `flags`
+     *
+     * Meant for a child created suspended, before it runs: with NS_PID it
+     * becomes PID 1 of the new namespace.
+     *
+     * @return 0 on success, -OR_EINVAL for bad flags or a PID namespace
+     *         nested too deep, -OR_ENOSPC when a table is full
+     */
+    int ns_unshare(uint64_t pid, uint32_t flags);
+
+    /**
+     * Set the clock offsets of the time namespace of `pid`
+     *
+     * @return 0 on success, -OR_EINVAL when `pid` is in the initial time
+     *         namespace or an offset would take a clock below zero,
+     *         -OR_EBUSY once the offsets are fixed
+     */
+    int ns_set_clock_offsets(uint64_t pid, const ns_clock_offsets_t *offsets);
+
+    // Offsets of the clocks `pid` reads from the kernel's, through every
+    // time namespace above its own
+    void ns_clock_offsets(uint64_t pid, ns_clock_offsets_t *offsets);
+
+    // A process was created by `parent` (0 for the kernel): it joins the
+    // parent's namespaces. Fails with -OR_ENOSPC when it cannot be numbered
+    int ns_process_fork(uint64_t parent, uint64_t child);
+
+    // Forget a process that is being destroyed
+    void ns_process_exit(uint64_t pid);
+
+    int ns_get_info(uint64_t pid, ns_info_t *info);
+
+    // Number of `pid` as seen by `viewer`, 0 when it lies outside the
+    // viewer's PID namespace
+    uint64_t ns_pid_to_viewer(uint64_t viewer, uint64_t pid);
+
+    // Global PID of what `viewer` numbers `local`, 0 when there is none
+    uint64_t ns_pid_from_viewer(uint64_t viewer, uint64_t local);
+
+    // Whether `pid` numbers processes differently from the kernel
+    bool ns_in_child_pid_ns(uint64_t pid);
+
+    // Whether namespace `id` of `kind` (one NS_* bit) still has members.
+    // Ids are reused once it has none
+    bool ns_in_use(uint32_t kind, uint32_t id);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NAMESPACE_H
@@ -15,6 +15,7 @@
 #include <orion/structures.h>
 #include <orion/mm.h>
 #include <orion/security.h>
+#include <orion/ipc.h>
 
 // ========================================
 // CONSTANTS AND DEFINITIONS
@@ -233,6 +234,9 @@ void process_destroy(process_t *process)
 
     kinfo("Destroying process '%s' (PID %llu)", process->name, (unsigned long long)process->pid);
 
+    // Callers waiting on its ports get an error rather than no answer
+    ipc_release_ports(process->pid);
+
     // Stop all threads
     thread_t *thread = process->threads;
     while (thread)
@@ -15,6 +15,7 @@
 #include <orion/mm.h>
 #include <orion/scheduler.h>
 #include <orion/ipc.h>
+#include <orion/oom.h>
 
 // ========================================
 // CONSTANTS AND DEFINITIONS
@@ -114,6 +115,9 @@ int start_fs_server(void)
         return -1;
     }
 
+    // Never an out-of-memory victim
+    oom_group_attach(OOM_GROUP_SYSTEM, fs_process->pid);
+
     // Register server
     spinlock_lock(&g_server_lock);
 
@@ -193,6 +197,9 @@ int start_network_server(void)
         return -1;
     }
 
+    // Never an out-of-memory victim
+    oom_group_attach(OOM_GROUP_SYSTEM, net_process->pid);
+
     // Register server
     spinlock_lock(&g_server_lock);
 
@@ -272,6 +279,9 @@ int start_device_manager(void)
         return -1;
     }
 
+    // Never an out-of-memory victim
+    oom_group_attach(OOM_GROUP_SYSTEM, dev_process->pid);
+
     // Register server
     spinlock_lock(&g_server_lock);
 
@@ -351,6 +361,9 @@ int start_ipc_server(void)
         return -1;
     }
 
+    // Never an out-of-memory victim
+    oom_group_attach(OOM_GROUP_SYSTEM, ipc_process->pid);
+
     // Register server
     spinlock_lock(&g_server_lock);
 
@@ -15,6 +15,7 @@
 #include <orion/mm.h>
 #include <orion/scheduler.h>
 #include <orion/security.h>
+#include <orion/aslr.h>
 
 // ========================================
 // CONSTANTS AND DEFINITIONS
@@ -130,37 +131,17 @@ thread_t *thread_create(process_t *process, const char *name, void (*entry_point
     thread->rbp = 0;
     memset(thread->registers, 0, sizeof(thread->registers));
 
-    // Allocate stack
+    // Allocate stack at a random address between guard pages
     uint64_t stack_pages = (THREAD_STACK_SIZE + PAGE_SIZE - 1) / PAGE_SIZE;
-    uint64_t stack_phys = pmm_alloc_pages(stack_pages);
-    if (!stack_phys)
-    {
-        kfree(thread);
-        spinlock_unlock(&g_thread_table_lock);
-        kerror("thread_create: Failed to allocate thread stack");
-        return NULL;
-    }
-
-    // Map stack into process virtual space
-    uint64_t stack_vaddr = vmm_alloc_pages(process->vm_space, stack_pages,
-                                           VM_FLAG_READ | VM_FLAG_WRITE | VM_FLAG_USER);
+    uint64_t stack_vaddr = aslr_alloc_stack(process->vm_space, process->pid, stack_pages);
     if (!stack_vaddr)
     {
-        pmm_free_pages(stack_phys, stack_pages);
         kfree(thread);
         spinlock_unlock(&g_thread_table_lock);
         kerror("thread_create: Failed to map thread stack");
         return NULL;
     }
 
-    // Map physical pages to virtual address
-    for (uint64_t i = 0; i < stack_pages; i++)
-    {
-        vmm_map_page(process->vm_space, stack_vaddr + i * PAGE_SIZE,
-                     stack_phys + i * PAGE_SIZE,
-                     VM_FLAG_READ | VM_FLAG_WRITE | VM_FLAG_USER);
-    }
-
     // Set up stack pointers
     thread->stack_base = stack_vaddr;
     thread->stack_size = stack_pages * PAGE_SIZE;
@@ -305,17 +286,8 @@ void thread_destroy(thread_t *thread)
     {
         uint64_t stack_pages = (thread->stack_size + PAGE_SIZE - 1) / PAGE_SIZE;
 
-        // Unmap from virtual space
-        for (uint64_t i = 0; i < stack_pages; i++)
-        {
-            uint64_t vaddr = thread->stack_base + i * PAGE_SIZE;
-            uint64_t paddr = vmm_get_physical_address(thread->parent_process->vm_space, vaddr);
-            if (paddr)
-            {
-                vmm_unmap_page(thread->parent_process->vm_space, vaddr);
-                pmm_free_pages(paddr, 1);
-            }
-        }
+        // Unmap from virtual space, dropping the guard pages around it
+        aslr_free_guarded(thread->parent_process->vm_space, thread->stack_base, stack_pages);
     }
 
     // Remove from thread table
@@ -0,0 +1,626 @@
+/*
+ * Orion Operating System - Real-Time Scheduling Class
+ *
+ * Threads with a real-time policy or an inherited priority have an entity
+ * in a fixed table, keyed by thread. Every CPU has a run list per
+ * priority and a bitmap of the non-empty ones; the scheduler asks this
+ * class for a thread before looking at its CFS tree.
+ *
+ * All state is under rt_lock, which nests inside the runqueue locks.
+ * Changing a priority outside a runqueue lock (setattr, inheritance)
+ * updates the entity first and then lets the scheduler requeue the thread.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/types.h>
+#include <orion/kernel.h>
+#include <orion/structures.h>
+#include <orion/constants.h>
+#include <orion/scheduler.h>
+#include <orion/sched_rt.h>
+
+// ========================================
+// STRUCTURES AND GLOBAL VARIABLES
+// ========================================
+
+typedef struct sched_rt_entity
+{
+    thread_t *thread;        // NULL for a free slot
+    uint32_t policy;         // SCHED_NORMAL when the priority is only inherited
+    uint32_t base_prio;      // Own priority, 0 for SCHED_NORMAL
+    uint32_t inherited_prio; // Highest priority donated over IPC
+    uint32_t queued_prio;    // Run list the thread is on, 0 if none
+    uint32_t queued_cpu;
+    bool slice_expired;      // RR: goes to the tail when next queued
+    bool throttled;          // Budget used up for this period
+    uint64_t slice_left_ns;  // RR: rest of the time slice
+    uint64_t runtime_ns;     // Budget per period
+    uint64_t period_ns;
+    uint64_t period_start;
+    uint64_t used_ns;        // Runtime charged in this period
+    thread_t *blocked_on;    // Server this thread waits for over IPC
+    struct sched_rt_entity *next; // Run list link
+} sched_rt_entity_t;
+
+typedef struct rt_queue
+{
+    uint64_t bitmap[2]; // Bit p set when run list p is not empty
+    sched_rt_entity_t *head[SCHED_RT_PRIO_MAX + 1];
+    sched_rt_entity_t *tail[SCHED_RT_PRIO_MAX + 1];
+    uint32_t nr_queued;
+} rt_queue_t;
+
+static sched_rt_entity_t rt_entities[SCHED_RT_MAX_THREADS];
+static rt_queue_t rt_queues[MAX_CPUS];
+static spinlock_t rt_lock = SPINLOCK_INIT;
+
+// Entities in use. Read unlocked so that systems without real-time
+// threads skip this class entirely
+static uint32_t rt_nr_entities = 0;
+
+// ========================================
+// ENTITIES
+// ========================================
+
+static sched_rt_entity_t *find_entity(const thread_t *thread)
+{
+    for (uint32_t i = 0; i < SCHED_RT_MAX_THREADS; i++)
+    {
+        if (rt_entities[i].thread == thread)
+        {
+            return &rt_entities[i];
+        }
+    }
+    return NULL;
+}
+
+static sched_rt_entity_t *get_entity(thread_t *thread)
+{
+    sched_rt_entity_t *entity = find_entity(thread);
+    if (entity)
+    {
+        return entity;
+    }
+
+    entity = find_entity(NULL);
+    if (!entity)
+    {
+        kwarn("sched_rt: no entity left for TID %llu", (unsigned long long)thread->tid);
+        return NULL;
+    }
+    memset(entity, 0, sizeof(*entity));
+    entity->thread = thread;
+    entity->policy = SCHED_NORMAL;
+    entity->slice_left_ns = SCHED_RR_TIMESLICE_NS;
+    entity->runtime_ns = SCHED_RT_DEFAULT_RUNTIME_NS;
+    entity->period_ns = SCHED_RT_DEFAULT_PERIOD_NS;
+    entity->period_start = arch_get_timestamp();
+    rt_nr_entities++;
+    return entity;
+}
+
+// Give the slot back once nothing makes the thread real-time any more
+static void put_entity(sched_rt_entity_t *entity)
+{
+    if (entity->policy != SCHED_NORMAL || entity->inherited_prio || entity->blocked_on || entity->queued_prio)
+    {
+        return;
+    }
+    memset(entity, 0, sizeof(*entity));
+    rt_nr_entities--;
+}
+
+// Start a new budget period once the current one is over
+static void refresh_budget(sched_rt_entity_t *entity, uint64_t now)
+{
+    if (now - entity->period_start < entity->period_ns)
+    {
+        return;
+    }
+    entity->period_start = now;
+    entity->used_ns = 0;
+    if (entity->throttled)
+    {
+        entity->throttled = false;
+        kdebug("sched_rt: TID %llu unthrottled", (unsigned long long)entity->thread->tid);
+    }
+}
+
+static uint32_t effective_prio(const sched_rt_entity_t *entity)
+{
+    if (entity->throttled)
+    {
+        return 0;
+    }
+    return MAX(entity->base_prio, entity->inherited_prio);
+}
+
+// ========================================
+// RUN LISTS
+// ========================================
+
+static void rt_queue_add(rt_queue_t *queue, sched_rt_entity_t *entity, uint32_t prio, bool head)
+{
+    if (head)
+    {
+        entity->next = queue->head[prio];
+        queue->head[prio] = entity;
+        if (!queue->tail[prio])
+        {
+            queue->tail[prio] = entity;
+        }
+    }
+    else
+    {
+        entity->next = NULL;
+        if (queue->tail[prio])
+        {
+            queue->tail[prio]->next = entity;
+        }
+        else
+        {
+            queue->head[prio] = entity;
+        }
+        queue->tail[prio] = entity;
+    }
+    queue->bitmap[prio / 64] |= 1ULL << (prio % 64);
+    queue->nr_queued++;
+}
+
+static void rt_queue_del(rt_queue_t *queue, sched_rt_entity_t *entity)
+{
+    uint32_t prio = entity->queued_prio;
+    sched_rt_entity_t *prev = NULL;
+    sched_rt_entity_t *cursor = queue->head[prio];
+
+    while (cursor && cursor != entity)
+    {
+        prev = cursor;
+        cursor = cursor->next;
+    }
+    if (!cursor)
+    {
+        return;
+    }
+
+    if (prev)
+    {
+        prev->next = entity->next;
+    }
+    else
+    {
+        queue->head[prio] = entity->next;
+    }
+    if (queue->tail[prio] == entity)
+    {
+        queue->tail[prio] = prev;
+    }
+    if (!queue->head[prio])
+    {
+        queue->bitmap[prio / 64] &= ~(1ULL << (prio % 64));
+    }
+
+    entity->next = NULL;
+    entity->queued_prio = 0;
+    queue->nr_queued--;
+}
+
+// Highest priority with a queued thread, 0 if none
+static uint32_t rt_queue_top(const rt_queue_t *queue)
+{
+    for (int word = 1; word >= 0; word--)
+    {
+        if (queue->bitmap[word])
+        {
+            return (uint32_t)(word * 64 + 63 - __builtin_clzll(queue->bitmap[word]));
+        }
+    }
+    return 0;
+}
+
+// ========================================
+// RUNQUEUE HOOKS
+// ========================================
+
+bool sched_rt_enqueue(uint32_t cpu, thread_t *thread, bool preempted)
+{
+    if (!rt_nr_entities || cpu >= MAX_CPUS)
+    {
+        return false;
+    }
+
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(thread);
+    uint32_t prio = 0;
+    if (entity)
+    {
+        refresh_budget(entity, arch_get_timestamp());
+        prio = effective_prio(entity);
+    }
+    if (prio)
+    {
+        rt_queue_add(&rt_queues[cpu], entity, prio, preempted && !entity->slice_expired);
+        entity->slice_expired = false;
+        entity->queued_prio = prio;
+        entity->queued_cpu = cpu;
+    }
+    spinlock_unlock(&rt_lock);
+
+    return prio != 0;
+}
+
+thread_t *sched_rt_dequeue_next(uint32_t cpu, uint32_t target)
+{
+    if (cpu >= MAX_CPUS || !rt_queues[cpu].nr_queued)
+    {
+        return NULL;
+    }
+
+    thread_t *thread = NULL;
+    spinlock_lock(&rt_lock);
+    rt_queue_t *queue = &rt_queues[cpu];
+    for (uint32_t prio = SCHED_RT_PRIO_MAX; prio >= SCHED_RT_PRIO_MIN && !thread; prio--)
+    {
+        if (!(queue->bitmap[prio / 64] & (1ULL << (prio % 64))))
+        {
+            continue;
+        }
+        for (sched_rt_entity_t *entity = queue->head[prio]; entity; entity = entity->next)
+        {
+            if (entity->thread->cpu_affinity & (1ULL << target))
+            {
+                thread = entity->thread;
+                rt_queue_del(queue, entity);
+                put_entity(entity);
+                break;
+            }
+        }
+    }
+    spinlock_unlock(&rt_lock);
+
+    return thread;
+}
+
+bool sched_rt_dequeue(uint32_t cpu, thread_t *thread)
+{
+    if (!rt_nr_entities || cpu >= MAX_CPUS)
+    {
+        return false;
+    }
+
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(thread);
+    bool queued = entity && entity->queued_prio && entity->queued_cpu == cpu;
+    if (queued)
+    {
+        rt_queue_del(&rt_queues[cpu], entity);
+        put_entity(entity);
+    }
+    spinlock_unlock(&rt_lock);
+
+    return queued;
+}
+
+bool sched_rt_queued_cpu(const thread_t *thread, uint32_t *cpu)
+{
+    if (!rt_nr_entities)
+    {
+        return false;
+    }
+
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(thread);
+    bool queued = entity && entity->queued_prio;
+    if (queued)
+    {
+        *cpu = entity->queued_cpu;
+    }
+    spinlock_unlock(&rt_lock);
+
+    return queued;
+}
+
+uint32_t sched_rt_nr_queued(uint32_t cpu)
+{
+    return cpu < MAX_CPUS ? rt_queues[cpu].nr_queued : 0;
+}
+
+bool sched_rt_tick(uint32_t cpu, thread_t *current, uint64_t delta_ns)
+{
+    if (!rt_nr_entities || cpu >= MAX_CPUS)
+    {
+        return false;
+    }
+
+    bool preempt = false;
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(current);
+    if (entity && effective_prio(entity))
+    {
+        refresh_budget(entity, arch_get_timestamp());
+        entity->used_ns += delta_ns;
+
+        if (entity->used_ns >= entity->runtime_ns)
+        {
+            // Runaway: run as a CFS thread until the period ends
+            entity->throttled = true;
+            preempt = true;
+            kwarn("sched_rt: TID %llu used its %llu ns budget, throttled for the rest of the period",
+                  (unsigned long long)current->tid, (unsigned long long)entity->runtime_ns);
+        }
+        else if (entity->policy == SCHED_RR && entity->base_prio >= entity->inherited_prio)
+        {
+            if (delta_ns >= entity->slice_left_ns)
+            {
+                entity->slice_left_ns = SCHED_RR_TIMESLICE_NS;
+                entity->slice_expired = true;
+                preempt = rt_queues[cpu].head[entity->base_prio] != NULL;
+            }
+            else
+            {
+                entity->slice_left_ns -= delta_ns;
+            }
+        }
+
+        if (rt_queue_top(&rt_queues[cpu]) > effective_prio(entity))
+        {
+            preempt = true;
+        }
+    }
+    spinlock_unlock(&rt_lock);
+
+    return preempt;
+}
+
+// ========================================
+// PUBLIC API
+// ========================================
+
+void sched_rt_init(void)
+{
+    memset(rt_entities, 0, sizeof(rt_entities));
+    memset(rt_queues, 0, sizeof(rt_queues));
+    rt_nr_entities = 0;
+
+    kinfo("Real-time scheduling: priorities %u-%u, default budget %llu ms per %llu ms", SCHED_RT_PRIO_MIN,
+          SCHED_RT_PRIO_MAX, (unsigned long long)(SCHED_RT_DEFAULT_RUNTIME_NS / 1000000),
+          (unsigned long long)(SCHED_RT_DEFAULT_PERIOD_NS / 1000000));
+}
+
+int sched_rt_setattr(thread_t *thread, const sched_rt_attr_t *attr)
+{
+    if (!thread || !attr)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint64_t period = attr->period_ns ? attr->period_ns : SCHED_RT_DEFAULT_PERIOD_NS;
+    uint64_t runtime = attr->runtime_ns ? attr->runtime_ns : period / 20 * 19;
+    switch (attr->policy)
+    {
+    case SCHED_NORMAL:
+        if (attr->priority != 0)
+        {
+            return -OR_EINVAL;
+        }
+        break;
+    case SCHED_FIFO:
+    case SCHED_RR:
+        if (attr->priority < SCHED_RT_PRIO_MIN || attr->priority > SCHED_RT_PRIO_MAX)
+        {
+            return -OR_EINVAL;
+        }
+        if (period < SCHED_RT_MIN_PERIOD_NS || period > SCHED_RT_MAX_PERIOD_NS ||
+            runtime < SCHED_RT_MIN_RUNTIME_NS || runtime > period)
+        {
+            return -OR_EINVAL;
+        }
+        break;
+    default:
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(thread);
+    if (attr->policy == SCHED_NORMAL)
+    {
+        if (entity)
+        {
+            entity->policy = SCHED_NORMAL;
+            entity->base_prio = 0;
+            put_entity(entity);
+        }
+    }
+    else
+    {
+        entity = get_entity(thread);
+        if (!entity)
+        {
+            spinlock_unlock(&rt_lock);
+            return -OR_ENOMEM;
+        }
+        entity->policy = attr->policy;
+        entity->base_prio = attr->priority;
+        entity->runtime_ns = runtime;
+        entity->period_ns = period;
+        entity->period_start = arch_get_timestamp();
+        entity->used_ns = 0;
+        entity->throttled = false;
+        entity->slice_left_ns = SCHED_RR_TIMESLICE_NS;
+    }
+    spinlock_unlock(&rt_lock);
+
+    scheduler_thread_prio_changed(thread);
+
+    kdebug("sched_rt: TID %llu policy %u priority %u", (unsigned long long)thread->tid, attr->policy,
+           attr->priority);
+    return OR_OK;
+}
+
+int sched_rt_getattr(thread_t *thread, sched_rt_attr_t *attr)
+{
+    if (!thread || !attr)
+    {
+        return -OR_EINVAL;
+    }
+
+    memset(attr, 0, sizeof(*attr));
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(thread);
+    if (entity && entity->policy != SCHED_NORMAL)
+    {
+        attr->policy = entity->policy;
+        attr->priority = entity->base_prio;
+        attr->runtime_ns = entity->runtime_ns;
+        attr->period_ns = entity->period_ns;
+    }
+    spinlock_unlock(&rt_lock);
+
+    return OR_OK;
+}
+
+uint32_t sched_rt_prio(const thread_t *thread)
+{
+    if (!thread || !rt_nr_entities)
+    {
+        return 0;
+    }
+
+    uint32_t prio = 0;
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = find_entity(thread);
+    if (entity)
+    {
+        refresh_budget(entity, arch_get_timestamp());
+        prio = effective_prio(entity);
+    }
+    spinlock_unlock(&rt_lock);
+
+    return prio;
+}
+
+void sched_rt_release(thread_t *thread)
+{
+    if (!thread || !rt_nr_entities)
+    {
+        return;
+    }
+
+    spinlock_lock(&rt_lock);
+    for (uint32_t i = 0; i < SCHED_RT_MAX_THREADS; i++)
+    {
+        sched_rt_entity_t *entity = &rt_entities[i];
+        if (entity->thread && entity->blocked_on == thread)
+        {
+            entity->blocked_on = NULL;
+            put_entity(entity);
+        }
+    }
+
+    sched_rt_entity_t *entity = find_entity(thread);
+    if (entity)
+    {
+        if (entity->queued_prio)
+        {
+            rt_queue_del(&rt_queues[entity->queued_cpu], entity);
+        }
+        memset(entity, 0, sizeof(*entity));
+        rt_nr_entities--;
+    }
+    spinlock_unlock(&rt_lock);
+}
+
+// ========================================
+// PRIORITY INHERITANCE
+// ========================================
+
+void sched_rt_inherit(thread_t *owner, uint32_t prio)
+{
+    if (!owner || !prio)
+    {
+        return;
+    }
+
+    thread_t *changed[SCHED_RT_PI_MAX_DEPTH];
+    uint32_t nr_changed = 0;
+
+    spinlock_lock(&rt_lock);
+    thread_t *thread = owner;
+    for (uint32_t depth = 0; thread && depth < SCHED_RT_PI_MAX_DEPTH; depth++)
+    {
+        sched_rt_entity_t *entity = get_entity(thread);
+        if (!entity || entity->inherited_prio >= prio)
+        {
+            break;
+        }
+        uint32_t before = effective_prio(entity);
+        entity->inherited_prio = prio;
+        if (effective_prio(entity) != before)
+        {
+            changed[nr_changed++] = thread;
+        }
+        // A server blocked on another one passes the priority along
+        thread = entity->blocked_on;
+    }
+    spinlock_unlock(&rt_lock);
+
+    for (uint32_t i = 0; i < nr_changed; i++)
+    {
+        scheduler_thread_prio_changed(changed[i]);
+    }
+}
+
+void sched_rt_inherit_reset(thread_t *owner, uint32_t prio)
+{
+    if (!owner || (!prio && !rt_nr_entities))
+    {
+        return;
+    }
+
+    bool changed = false;
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = prio ? get_entity(owner) : find_entity(owner);
+    if (entity)
+    {
+        uint32_t before = effective_prio(entity);
+        entity->inherited_prio = prio;
+        changed = effective_prio(entity) != before;
+        put_entity(entity);
+    }
+    spinlock_unlock(&rt_lock);
+
+    if (changed)
+    {
+        scheduler_thread_prio_changed(owner);
+    }
+}
+
+void sched_rt_block_on(thread_t *waiter, thread_t *owner)
+{
+    if (!waiter || waiter == owner)
+    {
+        return;
+    }
+
+    uint32_t prio = 0;
+    spinlock_lock(&rt_lock);
+    sched_rt_entity_t *entity = owner ? get_entity(waiter) : find_entity(waiter);
+    if (entity)
+    {
+        entity->blocked_on = owner;
+        prio = effective_prio(entity);
+        put_entity(entity);
+    }
+    spinlock_unlock(&rt_lock);
+
+    // The owner now works on behalf of the waiter
+    if (owner && prio)
+    {
+        sched_rt_inherit(owner, prio);
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Orion Operating System - Real-Time Scheduling Class
+ *
+ * SCHED_FIFO and SCHED_RR threads for latency-critical components such as
+ * the audio server and the input pipeline. A real-time thread has a fixed
+ * priority from 1 to 99 and always runs before CFS threads; among
+ * real-time threads the highest priority runs, FIFO threads until they
+ * block or yield, RR threads for a time slice before the next of their
+ * priority.
+ *
+ * Each real-time thread has a runtime budget per period. A thread using
+ * up its budget is throttled: it runs as a CFS thread until the period
+ * ends, so a runaway real-time task cannot starve the rest of the system.
+ *
+ * Synchronous IPC donates priority: a server receiving a request inherits
+ * its sender's priority until it asks for its next request, and a sender
+ * blocked on a server passes priority it inherits itself along to that
+ * server, so a high-priority client never waits behind a low-priority
+ * server preempted by medium-priority work.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_SCHED_RT_H
+#define ORION_SCHED_RT_H
+
+#include <orion/types.h>
+#include <orion/forward_decls.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Scheduling policies
+#define SCHED_NORMAL 0 // CFS
+#define SCHED_FIFO 1   // Real-time, runs until it blocks or yields
+#define SCHED_RR 2     // Real-time, round-robin within its priority
+
+// Real-time priorities, higher runs first. 0 is every CFS thread
+#define SCHED_RT_PRIO_MIN 1
+#define SCHED_RT_PRIO_MAX 99
+
+// Time slice of SCHED_RR threads
+#define SCHED_RR_TIMESLICE_NS 10000000ULL
+
+// Default budget: 950ms of every second, leaving CFS at least 5%
+#define SCHED_RT_DEFAULT_PERIOD_NS 1000000000ULL
+#define SCHED_RT_DEFAULT_RUNTIME_NS 950000000ULL
+
+// Accepted budget periods, and shortest runtime within one
+#define SCHED_RT_MIN_PERIOD_NS 1000000ULL
+#define SCHED_RT_MAX_PERIOD_NS 10000000000ULL
+#define SCHED_RT_MIN_RUNTIME_NS 100000ULL
+
+// Threads with a real-time policy or an inherited priority at once
+#define SCHED_RT_MAX_THREADS 256
+
+// Longest chain of blocked senders priority is passed along
+#define SCHED_RT_PI_MAX_DEPTH 8
+
+// SYS_SCHED_ATTR operations
+#define SCHED_ATTR_OP_GET 1
+#define SCHED_ATTR_OP_SET 2
+
+    typedef struct sched_rt_attr
+    {
+        uint32_t policy;     // SCHED_NORMAL, SCHED_FIFO or SCHED_RR
+        uint32_t priority;   // SCHED_RT_PRIO_MIN..MAX, 0 for SCHED_NORMAL
+        uint64_t runtime_ns; // Budget per period, 0 for the default
+        uint64_t period_ns;
+    } sched_rt_attr_t;
+
+    void sched_rt_init(void);
+
+    // Policy, priority and budget of a thread. Changing them requeues the
+    // thread if it is runnable
+    int sched_rt_setattr(thread_t *thread, const sched_rt_attr_t *attr);
+    int sched_rt_getattr(thread_t *thread, sched_rt_attr_t *attr);
+
+    // Priority the thread runs at: its own or an inherited one, 0 if it is
+    // a CFS thread or throttled. NULL is the idle CPU, 0
+    uint32_t sched_rt_prio(const thread_t *thread);
+
+    // Forget a thread that is exiting
+    void sched_rt_release(thread_t *thread);
+
+    // Runqueue hooks, called by the scheduler with the lock of `cpu`'s
+    // runqueue held.
+    //
+    // Queue a ready thread on `cpu` if it runs at a real-time priority;
+    // false leaves it to CFS. A thread that was preempted goes back to the
+    // head of its priority, unless it is RR and used up its time slice
+    bool sched_rt_enqueue(uint32_t cpu, thread_t *thread, bool preempted);
+    // Take the highest-priority thread queued on `cpu` that may run on
+    // `target`, NULL if there is none
+    thread_t *sched_rt_dequeue_next(uint32_t cpu, uint32_t target);
+    // Take a queued thread off `cpu`; false if it was not queued there
+    bool sched_rt_dequeue(uint32_t cpu, thread_t *thread);
+    // CPU whose queue holds the thread, false if it is not queued
+    bool sched_rt_queued_cpu(const thread_t *thread, uint32_t *cpu);
+    uint32_t sched_rt_nr_queued(uint32_t cpu);
+    // Charge `delta_ns` of running to the current thread of `cpu`. True if
+    // it must be preempted: its RR slice ended, its budget ran out or a
+    // higher priority is queued
+    bool sched_rt_tick(uint32_t cpu, thread_t *current, uint64_t delta_ns);
+
+    // Priority inheritance over IPC. Raise `owner` to at least `prio`,
+    // and along the chain of servers it is blocked on
+    void sched_rt_inherit(thread_t *owner, uint32_t prio);
+    // Set the priority `owner` inherits to exactly `prio`, 0 dropping it
+    void sched_rt_inherit_reset(thread_t *owner, uint32_t prio);
+    // Record that `waiter` waits for `owner`, NULL when it stops waiting
+    void sched_rt_block_on(thread_t *waiter, thread_t *owner);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_SCHED_RT_H
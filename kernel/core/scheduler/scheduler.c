@@ -16,6 +16,18 @@
 #include <orion/kernel.h>
 #include <orion/structures.h
 #include <orion/constants.h>
+#include <orion/cpufreq.h>
+#include <orion/aslr.h>
+#include <orion/vma.h>
+#include <orion/scheduler.h>
+#include <orion/smp.h>
+#include <orion/percpu.h>
+#include <orion/sched_rt.h>
+#include <orion/oom.h>
+#include <orion/ksm.h>
+#include <orion/namespace.h>
+#include <orion/mac.h>
+#include <orion/hypervisor.h>
 
 // All constants are defined in structures.h
 
@@ -89,6 +101,12 @@ static process_t *init_process = NULL;
 static uint64_t total_processes = 0;
 static uint64_t total_threads = 0;
 
+// Context saved when a CPU leaves its idle loop for a thread
+static thread_t idle_threads[MAX_CPUS];
+
+// Set by the tick and by reschedule IPIs, cleared when the CPU schedules
+static DEFINE_PER_CPU(bool, need_resched);
+
 // ========================================
 // CFS CONSTANTS AND MACROS
 // ========================================
@@ -165,16 +183,35 @@ void scheduler_tick(void)
     spinlock_lock(&rq->lock);
 
     thread_t *current = rq->current;
+
+    // Feed the frequency governor: the CPU was busy if a thread ran
+    cpufreq_update_util(current != NULL);
+
     if (!current)
     {
         spinlock_unlock(&rq->lock);
         return; // No current thread
     }
 
-    // Update current thread's virtual runtime
     uint64_t now = arch_get_timestamp();
     uint64_t delta = now - current->last_switch_time;
 
+    // Real-time threads run until the RT class preempts them
+    if (sched_rt_prio(current))
+    {
+        bool preempt = sched_rt_tick(cpu_id, current, delta);
+        current->last_switch_time = now;
+        current->actual_runtime += delta;
+        spinlock_unlock(&rq->lock);
+        if (preempt)
+        {
+            this_cpu_write(need_resched, true);
+        }
+        return;
+    }
+
+    // Update current thread's virtual runtime
+
     // Calculate weighted delta based on thread's priority
     uint64_t weight = sched_weights[current->priority + 20]; // priority range: -20 to +19
     uint64_t weighted_delta = (delta * NICE_0_WEIGHT) / weight;
@@ -209,6 +246,12 @@ void scheduler_tick(void)
                (unsigned long long)time_slice);
     }
 
+    // Preempt for any queued real-time thread
+    if (sched_rt_nr_queued(cpu_id))
+    {
+        should_preempt = true;
+    }
+
     // Preempt if there's a thread with lower virtual runtime
     if (rq->rb_root && rq->rb_root != current)
     {
@@ -248,25 +291,10 @@ void scheduler_tick(void)
     // Trigger reschedule if needed
     if (should_preempt)
     {
-        // Mark thread for rescheduling by setting state to ready
-        current->state = THREAD_STATE_READY;
-
-        // Send reschedule IPI to current CPU if SMP
-        extern bool smp_enabled;
-        extern void apic_send_ipi(uint32_t cpu_id, uint32_t vector);
-
-        if (smp_enabled)
-        {
-            // Send reschedule IPI to current CPU (assuming single CPU for now)
-            apic_send_ipi(0, 0x20); // Vector 0x20 for reschedule
-            kdebug("scheduler_tick: Sent reschedule IPI to CPU 0 for thread %llu",
-                   (unsigned long long)current->tid);
-        }
-        else
-        {
-            kdebug("scheduler_tick: Marked thread %llu for reschedule (no IPI)",
-                   (unsigned long long)current->tid);
-        }
+        // The tick runs on the CPU that owns the thread, so no IPI is needed
+        this_cpu_write(need_resched, true);
+        kdebug("scheduler_tick: Marked thread %llu for reschedule on CPU %u",
+               (unsigned long long)current->tid, cpu_id);
     }
 }
 
@@ -577,13 +605,9 @@ thread_t *scheduler_create_thread(process_t *process, uint64_t entry_point, uint
     return thread;
 }
 
-void scheduler_add_thread_to_rq(thread_t *thread)
+// Insert a CFS thread into `rq`, whose lock is held
+static void enqueue_fair(cpu_runqueue_t *rq, thread_t *thread)
 {
-    uint32_t cpu = arch_get_current_cpu();
-    cpu_runqueue_t *rq = &runqueues[cpu];
-
-    spinlock_lock(&rq->lock);
-
     // Update virtual runtime
     if (rq->rb_root)
     {
@@ -614,10 +638,103 @@ void scheduler_add_thread_to_rq(thread_t *thread)
 
     rb_insert_fixup(&rq->rb_root, thread);
 
-    rq->nr_running++;
     rq->load_weight += thread->nice_weight;
+}
 
+static uint32_t rq_cpu(const cpu_runqueue_t *rq)
+{
+    return (uint32_t)(rq - runqueues);
+}
+
+// Insert a ready thread into `rq`, whose lock is held: the RT class takes
+// it if it runs at a real-time priority, CFS otherwise. `preempted` is set
+// for a running thread put back because something more urgent runs
+static void enqueue_thread(cpu_runqueue_t *rq, thread_t *thread, bool preempted)
+{
+    if (!sched_rt_enqueue(rq_cpu(rq), thread, preempted))
+    {
+        enqueue_fair(rq, thread);
+    }
+    rq->nr_running++;
+}
+
+static bool cpu_allowed(const thread_t *thread, uint32_t cpu)
+{
+    return smp_cpu_is_online(cpu) && (thread->cpu_affinity & (1ULL << cpu));
+}
+
+// CPU for a real-time thread: the allowed CPU running the lowest
+// priority, the current CPU on a tie
+static uint32_t select_cpu_rt(const thread_t *thread, uint32_t prio)
+{
+    uint32_t self = arch_get_current_cpu();
+    uint32_t best = MAX_CPUS;
+    uint32_t best_prio = prio;
+
+    for (uint32_t cpu = 0; cpu < MAX_CPUS; cpu++)
+    {
+        if (!cpu_allowed(thread, cpu))
+        {
+            continue;
+        }
+        // Unlocked read of the running thread, as for queue lengths
+        uint32_t running = sched_rt_prio(runqueues[cpu].current);
+        if (best == MAX_CPUS || running < best_prio || (running == best_prio && cpu == self))
+        {
+            best = cpu;
+            best_prio = running;
+        }
+    }
+    return best == MAX_CPUS ? self : best;
+}
+
+// CPU whose runqueue receives a thread becoming ready: the current CPU
+// unless another allowed CPU has a shorter queue
+static uint32_t select_cpu(const thread_t *thread)
+{
+    uint32_t prio = sched_rt_prio(thread);
+    if (prio)
+    {
+        return select_cpu_rt(thread, prio);
+    }
+
+    uint32_t self = arch_get_current_cpu();
+    uint32_t best = cpu_allowed(thread, self) ? self : MAX_CPUS;
+
+    for (uint32_t cpu = 0; cpu < MAX_CPUS; cpu++)
+    {
+        if (cpu == self || !cpu_allowed(thread, cpu))
+        {
+            continue;
+        }
+        // Unlocked reads: a stale length only makes the choice less ideal
+        if (best == MAX_CPUS || runqueues[cpu].nr_running < runqueues[best].nr_running)
+        {
+            best = cpu;
+        }
+    }
+    return best == MAX_CPUS ? self : best;
+}
+
+void scheduler_add_thread_to_rq(thread_t *thread)
+{
+    uint32_t cpu = select_cpu(thread);
+    cpu_runqueue_t *rq = &runqueues[cpu];
+
+    spinlock_lock(&rq->lock);
+    enqueue_thread(rq, thread, false);
+    bool preempt = sched_rt_prio(thread) > sched_rt_prio(rq->current);
     spinlock_unlock(&rq->lock);
+
+    // Wake the target if it is idle or running something less urgent
+    if (cpu != arch_get_current_cpu())
+    {
+        smp_send_ipi(cpu, SMP_IPI_RESCHEDULE);
+    }
+    else if (preempt)
+    {
+        this_cpu_write(need_resched, true);
+    }
 }
 
 static thread_t *pick_next_thread(cpu_runqueue_t *rq)
@@ -686,6 +803,89 @@ static void remove_thread_from_rq(cpu_runqueue_t *rq, thread_t *thread)
     rq->load_weight -= thread->nice_weight;
 }
 
+// Take the next thread to run off `rq`, whose lock is held: real-time
+// threads first by priority, then the CFS thread with the smallest
+// virtual runtime. Only threads allowed on `cpu`, which will run it
+static thread_t *dequeue_next_thread(cpu_runqueue_t *rq, uint32_t cpu)
+{
+    thread_t *thread = sched_rt_dequeue_next(rq_cpu(rq), cpu);
+    if (thread)
+    {
+        rq->nr_running--;
+        return thread;
+    }
+
+    thread = pick_next_thread(rq);
+    if (thread && (thread->cpu_affinity & (1ULL << cpu)))
+    {
+        remove_thread_from_rq(rq, thread);
+        return thread;
+    }
+    return NULL;
+}
+
+// Runqueue a thread is queued on or running from, NULL if neither
+static cpu_runqueue_t *thread_rq(thread_t *thread)
+{
+    thread_t *root = thread;
+    while (root->rb_parent)
+    {
+        root = root->rb_parent;
+    }
+    for (uint32_t cpu = 0; cpu < MAX_CPUS; cpu++)
+    {
+        if (runqueues[cpu].current == thread || runqueues[cpu].rb_root == root)
+        {
+            return &runqueues[cpu];
+        }
+    }
+    return NULL;
+}
+
+// Work stealing for an idle CPU: take the most urgent thread of the
+// busiest other runqueue if it may run here. Victims are only trylocked,
+// so two CPUs stealing from each other cannot deadlock. Called without
+// any runqueue lock held
+static thread_t *steal_thread(uint32_t cpu)
+{
+    uint32_t victim = MAX_CPUS;
+    for (uint32_t other = 0; other < MAX_CPUS; other++)
+    {
+        if (other == cpu || !smp_cpu_is_online(other) || runqueues[other].nr_running == 0)
+        {
+            continue;
+        }
+        if (victim == MAX_CPUS || runqueues[other].nr_running > runqueues[victim].nr_running)
+        {
+            victim = other;
+        }
+    }
+    if (victim == MAX_CPUS)
+    {
+        return NULL;
+    }
+
+    cpu_runqueue_t *from = &runqueues[victim];
+    if (!spin_trylock(&from->lock))
+    {
+        return NULL;
+    }
+    thread_t *thread = dequeue_next_thread(from, cpu);
+    if (thread)
+    {
+        // Keep its lag relative to the queue it leaves
+        thread->virtual_runtime -= MIN(thread->virtual_runtime, from->min_vruntime);
+        thread->virtual_runtime += runqueues[cpu].min_vruntime;
+    }
+    spin_unlock(&from->lock);
+
+    if (thread)
+    {
+        kdebug("Work stealing: TID %llu from CPU %u to CPU %u", (unsigned long long)thread->tid, victim, cpu);
+    }
+    return thread;
+}
+
 // ========================================
 // SCHEDULER PRINCIPAL
 // ========================================
@@ -694,6 +894,8 @@ void scheduler_init(void)
 {
     kinfo("Initializing CFS scheduler");
 
+    sched_rt_init();
+
     // Initialize runqueues per CPU
     uint32_t cpu_count = arch_get_cpu_count();
     for (uint32_t i = 0; i < cpu_count && i < MAX_CPUS; i++)
@@ -726,47 +928,57 @@ void sched_yield(void)
 
     uint32_t cpu = arch_get_current_cpu();
     cpu_runqueue_t *rq = &runqueues[cpu];
+    bool preempted = this_cpu_read(need_resched);
+    this_cpu_write(need_resched, false);
 
     spinlock_lock(&rq->lock);
 
     thread_t *current = rq->current;
-    if (!current)
+    uint64_t now = arch_get_timestamp();
+
+    if (current)
     {
-        spinlock_unlock(&rq->lock);
-        return;
-    }
+        // Update runtime
+        uint64_t delta = now - current->last_switch_time;
+        current->actual_runtime += delta;
+        current->virtual_runtime += calc_delta_fair(delta, current->nice_weight);
+        current->last_switch_time = now;
 
-    // Update runtime
-    uint64_t now = arch_get_timestamp();
-    uint64_t delta = now - current->last_switch_time;
-    current->actual_runtime += delta;
-    current->virtual_runtime += calc_delta_fair(delta, current->nice_weight);
-    current->last_switch_time = now;
+        // Put back in runqueue if still RUNNING
+        if (current->state == THREAD_STATE_RUNNING)
+        {
+            current->state = THREAD_STATE_READY;
+            enqueue_thread(rq, current, preempted);
+        }
+    }
 
-    // Put back in runqueue if still RUNNING
-    if (current->state == THREAD_STATE_RUNNING)
+    // Select next thread, from another CPU if this one has nothing
+    thread_t *next = dequeue_next_thread(rq, cpu);
+    if (!next)
     {
-        current->state = THREAD_STATE_READY;
-        scheduler_add_thread_to_rq(current);
+        spinlock_unlock(&rq->lock);
+        next = steal_thread(cpu);
+        spinlock_lock(&rq->lock);
     }
 
-    // Select next thread
-    thread_t *next = pick_next_thread(rq);
     if (next)
     {
-        remove_thread_from_rq(rq, next);
         next->state = THREAD_STATE_RUNNING;
         next->last_switch_time = now;
         rq->current = next;
 
         if (next != current)
         {
-            kdebug("Context switch: TID %llu -> TID %llu",
+            kdebug("Context switch on CPU %u: TID %llu -> TID %llu", cpu,
                    (unsigned long long)(current ? current->tid : 0),
                    (unsigned long long)next->tid);
 
             // Perform context switch
-            arch_context_switch(current, next);
+            arch_context_switch(current ? current : &idle_threads[cpu], next);
+
+            // Back in this thread, possibly on another CPU, whose runqueue
+            // lock the thread that switched to us still holds
+            rq = &runqueues[arch_get_current_cpu()];
         }
     }
     else
@@ -777,12 +989,151 @@ void sched_yield(void)
     spinlock_unlock(&rq->lock);
 
     // If no thread to execute, idle
-    if (!rq->current)
+    if (!rq->current && !this_cpu_read(need_resched))
     {
         arch_cpu_idle();
     }
 }
 
+// Spin until `thread`, which marked itself blocked, has switched out of
+// the CPU it ran on and its context is saved
+static void wait_off_cpu(const thread_t *thread)
+{
+    for (uint32_t cpu = 0; cpu < MAX_CPUS; cpu++)
+    {
+        while (runqueues[cpu].current == thread)
+        {
+            arch_pause();
+        }
+    }
+}
+
+// Block the current thread and run `next`, a blocked thread, right away on
+// this CPU without going through a runqueue: the IPC call/reply fast
+// path. With `donate`, `next` runs on the current thread's scheduling
+// context: it takes its virtual runtime and so its place in CFS, and the
+// time it runs is charged to it from there
+void scheduler_handoff(thread_t *next, bool donate)
+{
+    uint32_t cpu = arch_get_current_cpu();
+    cpu_runqueue_t *rq = &runqueues[cpu];
+    thread_t *current = rq->current;
+
+    if (!current || !(next->cpu_affinity & (1ULL << cpu)))
+    {
+        // Not allowed here: the slow path
+        if (current)
+        {
+            current->state = THREAD_STATE_BLOCKED;
+        }
+        scheduler_wakeup_thread(next);
+        sched_yield();
+        return;
+    }
+
+    wait_off_cpu(next);
+
+    spinlock_lock(&rq->lock);
+    uint64_t now = arch_get_timestamp();
+    uint64_t delta = now - current->last_switch_time;
+    current->actual_runtime += delta;
+    current->virtual_runtime += calc_delta_fair(delta, current->nice_weight);
+    current->state = THREAD_STATE_BLOCKED;
+
+    if (donate)
+    {
+        next->virtual_runtime = current->virtual_runtime;
+    }
+    next->state = THREAD_STATE_RUNNING;
+    next->last_switch_time = now;
+    rq->current = next;
+
+    arch_context_switch(current, next);
+
+    // Back in this thread, possibly on another CPU, whose runqueue lock
+    // the thread that switched to us still holds
+    rq = &runqueues[arch_get_current_cpu()];
+    spinlock_unlock(&rq->lock);
+}
+
+void scheduler_idle_loop(void)
+{
+    for (;;)
+    {
+        // Background work that only runs when nothing else wants the CPU
+        ksm_idle();
+        sched_yield();
+    }
+}
+
+// Requeue a thread whose real-time priority changed, so that it is
+// queued in the class and at the priority it now runs at. A running
+// thread is rescheduled instead; a blocked one picks the change up when
+// it wakes
+void scheduler_thread_prio_changed(thread_t *thread)
+{
+    uint32_t cpu;
+    cpu_runqueue_t *rq;
+    if (sched_rt_queued_cpu(thread, &cpu))
+    {
+        rq = &runqueues[cpu];
+    }
+    else
+    {
+        rq = thread_rq(thread);
+        if (!rq)
+        {
+            return;
+        }
+        cpu = rq_cpu(rq);
+    }
+
+    spinlock_lock(&rq->lock);
+    bool requeued = false;
+    if (rq->current != thread && thread->state == THREAD_STATE_READY)
+    {
+        // The thread may have moved since it was looked up
+        if (sched_rt_dequeue(cpu, thread))
+        {
+            rq->nr_running--;
+            requeued = true;
+        }
+        else if (thread_rq(thread) == rq)
+        {
+            remove_thread_from_rq(rq, thread);
+            requeued = true;
+        }
+        if (requeued)
+        {
+            enqueue_thread(rq, thread, false);
+        }
+    }
+    spinlock_unlock(&rq->lock);
+
+    // Let the CPU reconsider what it runs
+    if (requeued || rq->current == thread)
+    {
+        if (cpu == arch_get_current_cpu())
+        {
+            this_cpu_write(need_resched, true);
+        }
+        else
+        {
+            smp_send_ipi(cpu, SMP_IPI_RESCHEDULE);
+        }
+    }
+}
+
+void scheduler_ipi_reschedule(void)
+{
+    this_cpu_write(need_resched, true);
+}
+
+bool scheduler_need_resched(void)
+{
+    return this_cpu_read(need_resched);
+}
+
 // ========================================
 // PUBLIC SCHEDULER API
 // ========================================
@@ -800,6 +1151,26 @@ thread_t *scheduler_get_current_thread(void)
     return runqueues[cpu].current;
 }
 
+thread_t *scheduler_find_thread(uint64_t tid)
+{
+    spinlock_lock(&process_list_lock);
+
+    for (process_t *proc = process_list; proc; proc = proc->next_sibling)
+    {
+        for (thread_t *thread = proc->threads; thread; thread = thread->next)
+        {
+            if (thread->tid == tid)
+            {
+                spinlock_unlock(&process_list_lock);
+                return thread;
+            }
+        }
+    }
+
+    spinlock_unlock(&process_list_lock);
+    return NULL;
+}
+
 process_t *scheduler_find_process(uint64_t pid)
 {
     spinlock_lock(&process_list_lock);
@@ -829,6 +1200,111 @@ void scheduler_add_process(process_t *process)
     scheduler_add_thread_to_rq(process->main_thread);
 }
 
+process_t *scheduler_fork_process(process_t *parent, uint64_t entry_point, uint64_t stack_pointer, uint64_t arg)
+{
+    if (!parent || !parent->vm_space)
+    {
+        return NULL;
+    }
+
+    process_t *child = scheduler_create_process();
+    if (!child)
+    {
+        return NULL;
+    }
+
+    // Replace the fresh address space by a copy-on-write copy of the parent's
+    vm_space_t *space = vmm_fork_space(parent->vm_space);
+    if (!space)
+    {
+        scheduler_destroy_process(child);
+        return NULL;
+    }
+    vmm_destroy_space(child->vm_space);
+    child->vm_space = space;
+
+    if (aslr_clone_space(parent->vm_space, space, child->pid) != OR_OK)
+    {
+        kwarn("fork: guard table full, PID %llu runs without stack guards", (unsigned long long)child->pid);
+    }
+
+    // The child joins the parent's namespaces and gets a number in each
+    // of its PID namespaces; it also keeps the parent's MAC label
+    if (ns_process_fork(parent->pid, child->pid) != OR_OK || mac_process_fork(parent->pid, child->pid) != OR_OK)
+    {
+        scheduler_destroy_process(child);
+        return NULL;
+    }
+
+    memcpy(child->name, parent->name, sizeof(child->name));
+    child->parent = parent;
+    child->entry_point = parent->entry_point;
+    child->heap_start = parent->heap_start;
+    child->brk = parent->brk;
+    child->code_base = parent->code_base;
+    child->code_size = parent->code_size;
+    child->data_base = parent->data_base;
+    child->data_size = parent->data_size;
+    child->stack_base = parent->stack_base;
+    child->stack_size = parent->stack_size;
+    child->stack_top = parent->stack_top;
+
+    // The kernel keeps no copy of the caller's user registers: the child
+    // starts at `entry_point`, where the caller's runtime restores them
+    if (!scheduler_create_thread(child, entry_point, stack_pointer, arg))
+    {
+        scheduler_destroy_process(child);
+        return NULL;
+    }
+
+    kdebug("Forked PID %llu into PID %llu", (unsigned long long)parent->pid, (unsigned long long)child->pid);
+    return child;
+}
+
+int scheduler_get_process_info(uint64_t after_pid, proc_info_t *info)
+{
+    if (!info)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&process_list_lock);
+    process_t *found = NULL;
+    for (process_t *proc = process_list; proc; proc = proc->next_sibling)
+    {
+        if (proc->pid > after_pid && (!found || proc->pid < found->pid))
+        {
+            found = proc;
+        }
+    }
+    if (!found)
+    {
+        spinlock_unlock(&process_list_lock);
+        return -OR_ENOENT;
+    }
+
+    memset(info, 0, sizeof(*info));
+    info->pid = found->pid;
+    info->ppid = found->parent ? found->parent->pid : 0;
+    info->state = found->state;
+    info->threads = found->thread_count;
+    for (size_t i = 0; i < sizeof(info->name) - 1 && found->name[i]; i++)
+    {
+        info->name[i] = found->name[i];
+    }
+
+    vm_usage_t usage;
+    if (found->vm_space && vmm_get_usage(found->vm_space, &usage) == OR_OK)
+    {
+        info->rss = usage.rss;
+        info->uss = usage.uss;
+        info->pss = usage.pss;
+        info->shared = usage.shared;
+    }
+    spinlock_unlock(&process_list_lock);
+    return OR_OK;
+}
+
 void scheduler_block_current_process(void)
 {
     uint32_t cpu = arch_get_current_cpu();
@@ -860,6 +1336,23 @@ void scheduler_wakeup_process(process_t *process)
     }
 }
 
+// Make a blocked thread ready, once it is off the CPU it blocked on
+void scheduler_wakeup_thread(thread_t *thread)
+{
+    if (!thread || thread->state != THREAD_STATE_BLOCKED)
+    {
+        return;
+    }
+
+    wait_off_cpu(thread);
+    thread->state = THREAD_STATE_READY;
+    if (thread->parent_process)
+    {
+        thread->parent_process->state = PROCESS_STATE_READY;
+    }
+    scheduler_add_thread_to_rq(thread);
+}
+
 void scheduler_sleep_ns(uint64_t nanoseconds)
 {
     uint32_t cpu = arch_get_current_cpu();
@@ -888,15 +1381,39 @@ void scheduler_destroy_process(process_t *process)
     if (!process)
         return;
 
+    oom_process_exit(process->pid);
+    ns_process_exit(process->pid);
+    mac_process_exit(process->pid);
+    hv_process_exit(process->pid);
+
     // Nettoyer tous les threads
     thread_t *thread = process->threads;
     while (thread)
     {
         thread_t *next = thread->next;
 
-        // Remove from runqueue if necessary
-        uint32_t cpu = arch_get_current_cpu();
-        cpu_runqueue_t *rq = &runqueues[cpu];
+        // Off the real-time run list it may be queued on
+        uint32_t rt_cpu;
+        if (sched_rt_queued_cpu(thread, &rt_cpu))
+        {
+            spinlock_lock(&runqueues[rt_cpu].lock);
+            if (sched_rt_dequeue(rt_cpu, thread))
+            {
+                runqueues[rt_cpu].nr_running--;
+            }
+            spinlock_unlock(&runqueues[rt_cpu].lock);
+        }
+        sched_rt_release(thread);
+
+        // Remove from the runqueue of whichever CPU holds it
+        cpu_runqueue_t *rq = thread_rq(thread);
+        if (!rq)
+        {
+            kfree(thread);
+            total_threads--;
+            thread = next;
+            continue;
+        }
 
         spinlock_lock(&rq->lock);
         if (rq->current == thread)
@@ -1149,11 +1666,6 @@ int elf_load_process(process_t *process, const char *path)
 
     // For now, create a simple placeholder process with basic memory layout
 
-    // Set up basic process memory layout
-    process->entry_point = 0x400000;        // Standard ELF entry point
-    process->heap_start = 0x600000;         // Heap starts after code/data
-    process->stack_top = 0x7FFFFFFFF000ULL; // Stack at top of user space
-
     // Allocate and map basic pages for the process
     vm_space_t *vm_space = process->vm_space;
     if (!vm_space)
@@ -1161,39 +1673,52 @@ int elf_load_process(process_t *process, const char *path)
         return -OR_ENOMEM;
     }
 
-    // Map code segment (executable, read-only)
+    // Code and data segments sit back to back at a random load bias; their
+    // pages are allocated when the process first touches them
     uint64_t code_pages = 16; // 64KB for code
-    uint64_t code_vaddr = vmm_alloc_pages(vm_space, code_pages,
-                                          VM_FLAG_READ | VM_FLAG_EXEC | VM_FLAG_USER);
-    if (!code_vaddr)
-    {
-        kerror("Failed to allocate code pages for process");
+    uint64_t data_pages = 16; // 64KB for data
+    uint64_t image_size = (code_pages + data_pages) * PAGE_SIZE;
+    uint64_t code_vaddr = aslr_random_base(ASLR_CODE_BASE, ASLR_CODE_BASE + ASLR_CODE_RANGE, image_size);
+    vma_t segment = {0};
+    segment.start = code_vaddr;
+    segment.end = code_vaddr + code_pages * PAGE_SIZE;
+    segment.prot = VM_FLAG_READ | VM_FLAG_EXEC | VM_FLAG_USER;
+    segment.type = VMA_ANONYMOUS;
+    segment.flags = VMA_LAZY;
+    if (vma_insert(vm_space, &segment) != OR_OK)
+    {
+        kerror("Failed to map code segment for process");
         return -OR_ENOMEM;
     }
 
-    // Map data segment (read-write)
-    uint64_t data_pages = 16; // 64KB for data
-    uint64_t data_vaddr = vmm_alloc_pages(vm_space, data_pages,
-                                          VM_FLAG_READ | VM_FLAG_WRITE | VM_FLAG_USER);
-    if (!data_vaddr)
+    uint64_t data_vaddr = segment.end;
+    segment.start = data_vaddr;
+    segment.end = data_vaddr + data_pages * PAGE_SIZE;
+    segment.prot = VM_FLAG_READ | VM_FLAG_WRITE | VM_FLAG_USER;
+    if (vma_insert(vm_space, &segment) != OR_OK)
     {
-        kerror("Failed to allocate data pages for process");
-        vmm_free_pages(vm_space, code_vaddr, code_pages);
+        kerror("Failed to map data segment for process");
+        vma_unmap(vm_space, code_vaddr, code_pages * PAGE_SIZE);
         return -OR_ENOMEM;
     }
 
-    // Map stack (read-write, guard pages)
+    // Map stack (read-write) at a random address between guard pages
     uint64_t stack_pages = 32; // 128KB stack
-    uint64_t stack_vaddr = vmm_alloc_pages(vm_space, stack_pages,
-                                           VM_FLAG_READ | VM_FLAG_WRITE | VM_FLAG_USER);
+    uint64_t stack_vaddr = aslr_alloc_stack(vm_space, process->pid, stack_pages);
     if (!stack_vaddr)
     {
         kerror("Failed to allocate stack pages for process");
-        vmm_free_pages(vm_space, code_vaddr, code_pages);
-        vmm_free_pages(vm_space, data_vaddr, data_pages);
+        vma_unmap(vm_space, code_vaddr, image_size);
         return -OR_ENOMEM;
     }
 
+    // The image is linked at ASLR_CODE_BASE; the heap starts at a random
+    // distance above it
+    uint64_t image_end = data_vaddr + data_pages * PAGE_SIZE;
+    process->entry_point = code_vaddr + (elf_header.entry - ASLR_CODE_BASE);
+    process->heap_start = aslr_random_base(image_end, image_end + ASLR_HEAP_GAP, PAGE_SIZE);
+    process->stack_top = stack_vaddr + stack_pages * PAGE_SIZE;
+
     // Set up process memory regions
     process->code_base = code_vaddr;
     process->code_size = code_pages * PAGE_SIZE;
@@ -1539,6 +2064,7 @@ void thread_exit(int exit_code)
     thread_t *current = scheduler_get_current_thread();
     if (current)
     {
+        sched_rt_release(current);
         current->state = THREAD_STATE_TERMINATED;
         if (current->parent_process)
         {
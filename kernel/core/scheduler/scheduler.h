@@ -27,6 +27,47 @@ extern "C"
     void scheduler_remove_thread_from_rq(thread_t *thread);
     thread_t *scheduler_get_next_thread(void);
     process_t *scheduler_get_current_process(void);
+    thread_t *scheduler_find_thread(uint64_t tid);
+
+    // Blocking and waking single threads. scheduler_handoff blocks the
+    // current thread and switches straight to `next`; with `donate`, `next`
+    // runs on the current thread's CFS position
+    void scheduler_wakeup_thread(thread_t *thread);
+    void scheduler_handoff(thread_t *next, bool donate);
+
+    // Requeue a thread after its real-time priority changed
+    void scheduler_thread_prio_changed(thread_t *thread);
+
+    // SMP: idle loop run by every CPU once it has nothing else to do, and
+    // the handler of reschedule IPIs
+    void scheduler_idle_loop(void);
+    void scheduler_ipi_reschedule(void);
+    bool scheduler_need_resched(void);
+
+    // Fork `parent`: the child gets a copy-on-write copy of its address
+    // space and one thread starting at `entry_point` on `stack_pointer`
+    // with `arg` as first argument. The child is not queued yet
+    process_t *scheduler_fork_process(process_t *parent, uint64_t entry_point, uint64_t stack_pointer,
+                                      uint64_t arg);
+
+    // Process listing, also the SYS_PROC_INFO ABI. Memory is in bytes, see
+    // vm_usage_t for what each figure counts
+    typedef struct proc_info
+    {
+        uint64_t pid;
+        uint64_t ppid;
+        uint32_t state; // PROCESS_STATE_*
+        uint32_t threads;
+        char name[32];
+        uint64_t rss;
+        uint64_t uss;
+        uint64_t pss;
+        uint64_t shared;
+    } proc_info_t;
+
+    // Information on the process with the lowest PID above `after_pid`;
+    // -OR_ENOENT once there is none
+    int scheduler_get_process_info(uint64_t after_pid, proc_info_t *info);
 
     // Handle structure
     typedef struct
@@ -0,0 +1,449 @@
+/*
+ * Orion Operating System - Configuration Apply
+ *
+ * Turns the difference between the configuration the services run with
+ * and a new one into steps, and runs them all or none. Each step reads
+ * what it is about to change before changing it and leaves behind the
+ * step that puts it back; when a step fails, the steps already taken are
+ * reversed newest first, so the network, file system and storage servers
+ * end up as they were before the apply started.
+ *
+ * Steps run in an order that keeps dependencies working: shares are
+ * unmounted first, interfaces are configured before new shares are
+ * mounted over them, and access lists come last. Interfaces left out of
+ * the new configuration keep their settings; mounts and access lists
+ * left out are removed (access lists go back to the driver default).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+use crate::protocol::{STATUS_EIO, STATUS_ENOENT, STATUS_ESTALE, STATUS_OK};
+use crate::schema::{AccessEntry, InterfaceConfig, MountConfig, SystemConfig, ACL_TARGET_POOL, IFACE_NAME_SIZE};
+
+// Network server interface requests (services/net/iface_ipc.h)
+const IFACE_OP_LIST: u32 = 16;
+const IFACE_OP_CONFIGURE: u32 = 17;
+const IFACE_SET_STATE: u32 = 1 << 0;
+const IFACE_SET_MTU: u32 = 1 << 1;
+const IFACE_RECORD_SIZE: usize = 120;
+const IFACE_STATE_DOWN: u32 = 0;
+const IFACE_STATE_UP: u32 = 1;
+const IFACE_STATE_RUNNING: u32 = 2;
+
+// File system server mount requests (services/fs/src/main.rs)
+const FS_OP_MOUNT: u32 = 0x46;
+const FS_OP_UNMOUNT: u32 = 0x47;
+const FS_MOUNT_NFS: u32 = 1;
+
+// LVM control protocol (drivers/block/src/lvm.rs)
+const LVM_CTRL_GET_ACL: u32 = 9;
+const LVM_CTRL_SET_ACL: u32 = 10;
+
+/// The servers a configuration is applied to. Errors are negative statuses
+pub trait Services {
+    /// Whether the interface is up, and its MTU
+    fn interface(&mut self, name: &str) -> Result<(bool, u32), i32>;
+    fn configure_interface(&mut self, name: &str, up: Option<bool>, mtu: Option<u32>) -> Result<(), i32>;
+    fn mount(&mut self, mount: &MountConfig) -> Result<(), i32>;
+    fn unmount(&mut self, path: &str) -> Result<(), i32>;
+    fn access(&mut self, target: u32, name: &str) -> Result<Vec<AccessEntry>, i32>;
+    fn set_access(&mut self, target: u32, name: &str, entries: &[AccessEntry]) -> Result<(), i32>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Interface(InterfaceConfig),
+    Unmount(MountConfig),
+    Mount(MountConfig),
+    Access { target: u32, name: String, entries: Vec<AccessEntry> },
+}
+
+impl Step {
+    /// What the step touches, for error messages and the audit trail
+    pub fn describe(&self) -> String {
+        match self {
+            Step::Interface(interface) => format!("interface {}", interface.name),
+            Step::Unmount(mount) => format!("unmount {}", mount.path),
+            Step::Mount(mount) => format!("mount {}", mount.path),
+            Step::Access { target, name, .. } => {
+                format!("{} {} access list", if *target == ACL_TARGET_POOL { "pool" } else { "volume" }, name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyError {
+    /// Step that failed
+    pub step: String,
+    pub status: i32,
+    /// False when a reversal failed too and some change is still in place
+    pub rolled_back: bool,
+}
+
+/// Steps taking the services from `from` to `to`
+pub fn plan(from: &SystemConfig, to: &SystemConfig) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for mount in from.mounts.iter() {
+        if !to.mounts.contains(mount) {
+            steps.push(Step::Unmount(mount.clone()));
+        }
+    }
+    for interface in to.interfaces.iter() {
+        if !from.interfaces.contains(interface) {
+            steps.push(Step::Interface(interface.clone()));
+        }
+    }
+    for mount in to.mounts.iter() {
+        if !from.mounts.contains(mount) {
+            steps.push(Step::Mount(mount.clone()));
+        }
+    }
+    for access in to.access.iter() {
+        if !from.access.contains(access) {
+            steps.push(Step::Access { target: access.target, name: access.name.clone(), entries: access.entries.clone() });
+        }
+    }
+    for access in from.access.iter() {
+        if !to.access.iter().any(|other| other.target == access.target && other.name == access.name) {
+            steps.push(Step::Access { target: access.target, name: access.name.clone(), entries: Vec::new() });
+        }
+    }
+    steps
+}
+
+/// Take one step; Ok with the step that undoes it
+fn take(services: &mut impl Services, step: &Step) -> Result<Step, i32> {
+    match step {
+        Step::Interface(interface) => {
+            let (up, mtu) = services.interface(&interface.name)?;
+            services.configure_interface(&interface.name, interface.up, interface.mtu)?;
+            Ok(Step::Interface(InterfaceConfig {
+                name: interface.name.clone(),
+                up: interface.up.map(|_| up),
+                mtu: interface.mtu.map(|_| mtu),
+            }))
+        }
+        Step::Unmount(mount) => {
+            services.unmount(&mount.path)?;
+            Ok(Step::Mount(mount.clone()))
+        }
+        Step::Mount(mount) => {
+            services.mount(mount)?;
+            Ok(Step::Unmount(mount.clone()))
+        }
+        Step::Access { target, name, entries } => {
+            let previous = services.access(*target, name)?;
+            services.set_access(*target, name, entries)?;
+            Ok(Step::Access { target: *target, name: name.clone(), entries: previous })
+        }
+    }
+}
+
+/// Run every step or none; Ok with the number of steps taken
+pub fn execute(services: &mut impl Services, steps: &[Step]) -> Result<usize, ApplyError> {
+    let mut undo = Vec::with_capacity(steps.len());
+    for step in steps {
+        match take(services, step) {
+            Ok(reverse) => undo.push(reverse),
+            Err(status) => {
+                let mut rolled_back = true;
+                for reverse in undo.iter().rev() {
+                    rolled_back &= take(services, reverse).is_ok();
+                }
+                return Err(ApplyError { step: step.describe(), status, rolled_back });
+            }
+        }
+    }
+    Ok(steps.len())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Entries and generation of an LVM GET_ACL reply
+fn decode_acl(payload: &[u8]) -> Option<(u64, Vec<AccessEntry>)> {
+    let generation = read_u64(payload, 0)?;
+    let count = read_u32(payload, 8)? as usize;
+    let entries = (0..count)
+        .map(|index| {
+            let offset = 12 + index * 20;
+            Some(AccessEntry {
+                principal: read_u32(payload, offset)?,
+                id: read_u64(payload, offset + 4)?,
+                allow: read_u32(payload, offset + 12)?,
+                deny: read_u32(payload, offset + 16)?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (payload.len() == 12 + count * 20).then_some((generation, entries))
+}
+
+/// The network, file system and storage servers, over IPC
+pub struct IpcServices {
+    net: IpcChannel,
+    fs: IpcChannel,
+    storage: IpcChannel,
+}
+
+impl IpcServices {
+    pub fn connect() -> Self {
+        Self {
+            net: IpcChannel::connect("net"),
+            fs: IpcChannel::connect("fs"),
+            storage: IpcChannel::connect("lvm-advanced"),
+        }
+    }
+
+    fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = channel.call(request).map_err(|_| STATUS_EIO)?;
+        if response.len() < 4 {
+            return Err(STATUS_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn acl(&mut self, target: u32, name: &str) -> Result<(u64, Vec<AccessEntry>), i32> {
+        let mut request = LVM_CTRL_GET_ACL.to_le_bytes().to_vec();
+        request.extend_from_slice(&target.to_le_bytes());
+        put_string(&mut request, name);
+        decode_acl(&Self::call(&mut self.storage, &request)?).ok_or(STATUS_EIO)
+    }
+}
+
+impl Services for IpcServices {
+    fn interface(&mut self, name: &str) -> Result<(bool, u32), i32> {
+        let payload = Self::call(&mut self.net, &IFACE_OP_LIST.to_le_bytes())?;
+        for record in payload.chunks_exact(IFACE_RECORD_SIZE) {
+            let length = record[..IFACE_NAME_SIZE].iter().position(|byte| *byte == 0).unwrap_or(IFACE_NAME_SIZE);
+            if &record[..length] == name.as_bytes() {
+                let state = read_u32(record, 36).ok_or(STATUS_EIO)?;
+                let up = state == IFACE_STATE_UP || state == IFACE_STATE_RUNNING;
+                return Ok((up, read_u32(record, 40).ok_or(STATUS_EIO)?));
+            }
+        }
+        Err(STATUS_ENOENT)
+    }
+
+    fn configure_interface(&mut self, name: &str, up: Option<bool>, mtu: Option<u32>) -> Result<(), i32> {
+        let mut mask = 0;
+        if up.is_some() {
+            mask |= IFACE_SET_STATE;
+        }
+        if mtu.is_some() {
+            mask |= IFACE_SET_MTU;
+        }
+        let state = if up == Some(true) { IFACE_STATE_UP } else { IFACE_STATE_DOWN };
+
+        let mut request = IFACE_OP_CONFIGURE.to_le_bytes().to_vec();
+        request.extend_from_slice(name.as_bytes());
+        request.resize(4 + IFACE_NAME_SIZE, 0);
+        request.extend_from_slice(&mask.to_le_bytes());
+        request.extend_from_slice(&state.to_le_bytes());
+        request.extend_from_slice(&mtu.unwrap_or(0).to_le_bytes());
+        Self::call(&mut self.net, &request).map(|_| ())
+    }
+
+    fn mount(&mut self, mount: &MountConfig) -> Result<(), i32> {
+        let mut request = FS_OP_MOUNT.to_le_bytes().to_vec();
+        request.extend_from_slice(&FS_MOUNT_NFS.to_le_bytes());
+        put_string(&mut request, &mount.path);
+        put_string(&mut request, &mount.source);
+        put_string(&mut request, &mount.options);
+        Self::call(&mut self.fs, &request).map(|_| ())
+    }
+
+    fn unmount(&mut self, path: &str) -> Result<(), i32> {
+        let mut request = FS_OP_UNMOUNT.to_le_bytes().to_vec();
+        put_string(&mut request, path);
+        Self::call(&mut self.fs, &request).map(|_| ())
+    }
+
+    fn access(&mut self, target: u32, name: &str) -> Result<Vec<AccessEntry>, i32> {
+        self.acl(target, name).map(|(_, entries)| entries)
+    }
+
+    fn set_access(&mut self, target: u32, name: &str, entries: &[AccessEntry]) -> Result<(), i32> {
+        // The driver refuses an update based on a stale generation; read
+        // it again once if an administrator got in between
+        let mut attempts = 2;
+        loop {
+            let (generation, _) = self.acl(target, name)?;
+            let mut request = LVM_CTRL_SET_ACL.to_le_bytes().to_vec();
+            request.extend_from_slice(&target.to_le_bytes());
+            put_string(&mut request, name);
+            request.extend_from_slice(&generation.to_le_bytes());
+            request.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            for entry in entries {
+                request.extend_from_slice(&entry.principal.to_le_bytes());
+                request.extend_from_slice(&entry.id.to_le_bytes());
+                request.extend_from_slice(&entry.allow.to_le_bytes());
+                request.extend_from_slice(&entry.deny.to_le_bytes());
+            }
+            attempts -= 1;
+            match Self::call(&mut self.storage, &request) {
+                Err(STATUS_ESTALE) if attempts > 0 => continue,
+                result => return result.map(|_| ()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::schema::{ACL_PRINCIPAL_EVERYONE, ACL_TARGET_VOLUME};
+    use alloc::collections::BTreeMap;
+
+    /// Servers kept in memory, failing the operations named in `fail`
+    #[derive(Default)]
+    pub struct FakeServices {
+        pub interfaces: BTreeMap<String, (bool, u32)>,
+        pub mounts: Vec<MountConfig>,
+        pub access: BTreeMap<(u32, String), Vec<AccessEntry>>,
+        pub fail: Vec<String>,
+    }
+
+    impl FakeServices {
+        pub fn with_interface(name: &str) -> Self {
+            let mut services = Self::default();
+            services.interfaces.insert(String::from(name), (false, 1500));
+            services
+        }
+
+        fn check(&self, operation: String) -> Result<(), i32> {
+            if self.fail.contains(&operation) {
+                return Err(STATUS_EIO);
+            }
+            Ok(())
+        }
+    }
+
+    impl Services for &mut FakeServices {
+        fn interface(&mut self, name: &str) -> Result<(bool, u32), i32> {
+            self.interfaces.get(name).copied().ok_or(STATUS_ENOENT)
+        }
+
+        fn configure_interface(&mut self, name: &str, up: Option<bool>, mtu: Option<u32>) -> Result<(), i32> {
+            self.check(format!("interface {}", name))?;
+            let interface = self.interfaces.get_mut(name).ok_or(STATUS_ENOENT)?;
+            interface.0 = up.unwrap_or(interface.0);
+            interface.1 = mtu.unwrap_or(interface.1);
+            Ok(())
+        }
+
+        fn mount(&mut self, mount: &MountConfig) -> Result<(), i32> {
+            self.check(format!("mount {}", mount.path))?;
+            self.mounts.push(mount.clone());
+            Ok(())
+        }
+
+        fn unmount(&mut self, path: &str) -> Result<(), i32> {
+            self.check(format!("unmount {}", path))?;
+            let index = self.mounts.iter().position(|mount| mount.path == path).ok_or(STATUS_ENOENT)?;
+            self.mounts.remove(index);
+            Ok(())
+        }
+
+        fn access(&mut self, target: u32, name: &str) -> Result<Vec<AccessEntry>, i32> {
+            Ok(self.access.get(&(target, String::from(name))).cloned().unwrap_or_default())
+        }
+
+        fn set_access(&mut self, target: u32, name: &str, entries: &[AccessEntry]) -> Result<(), i32> {
+            self.check(format!("access {}", name))?;
+            self.access.insert((target, String::from(name)), entries.to_vec());
+            Ok(())
+        }
+    }
+
+    fn config(mtu: u32, share: &str) -> SystemConfig {
+        SystemConfig {
+            interfaces: alloc::vec![InterfaceConfig { name: String::from("eth0"), up: Some(true), mtu: Some(mtu) }],
+            mounts: alloc::vec![MountConfig {
+                path: format!("/mnt/{}", share),
+                source: format!("nas:/{}", share),
+                options: String::new(),
+            }],
+            access: alloc::vec![crate::schema::AccessConfig {
+                target: ACL_TARGET_VOLUME,
+                name: String::from("home"),
+                entries: alloc::vec![AccessEntry { principal: ACL_PRINCIPAL_EVERYONE, id: 0, allow: 1, deny: 0 }],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_plans_only_the_differences() {
+        let from = config(1500, "backup");
+        assert!(plan(&from, &from).is_empty());
+
+        let to = config(9000, "media");
+        let steps: Vec<String> = plan(&from, &to).iter().map(Step::describe).collect();
+        assert_eq!(steps, ["unmount /mnt/backup", "interface eth0", "mount /mnt/media"]);
+
+        let steps = plan(&from, &SystemConfig::default());
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1], Step::Access { target: ACL_TARGET_VOLUME, name: String::from("home"), entries: Vec::new() });
+    }
+
+    #[test]
+    fn test_applies_everything_or_nothing() {
+        let mut services = FakeServices::with_interface("eth0");
+        let first = config(1500, "backup");
+        assert_eq!(execute(&mut &mut services, &plan(&SystemConfig::default(), &first)), Ok(3));
+        assert_eq!(services.interfaces["eth0"], (true, 1500));
+
+        // The new share cannot be mounted: the old one comes back and the
+        // interface gets its MTU back
+        services.fail.push(String::from("mount /mnt/media"));
+        let error = execute(&mut &mut services, &plan(&first, &config(9000, "media"))).unwrap_err();
+        assert_eq!(error, ApplyError { step: String::from("mount /mnt/media"), status: STATUS_EIO, rolled_back: true });
+        assert_eq!(services.interfaces["eth0"], (true, 1500));
+        assert_eq!(services.mounts, first.mounts);
+
+        // A reversal that fails as well is reported
+        services.fail.push(String::from("mount /mnt/backup"));
+        let error = execute(&mut &mut services, &plan(&first, &config(9000, "media"))).unwrap_err();
+        assert!(!error.rolled_back);
+    }
+
+    #[test]
+    fn test_decodes_access_lists() {
+        let mut payload = 5u64.to_le_bytes().to_vec();
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        for value in [2u32, 0x2a, 0, 3, 0] {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        let (generation, entries) = decode_acl(&payload).unwrap();
+        assert_eq!(generation, 5);
+        assert_eq!(entries[0], AccessEntry { principal: 2, id: 0x2a, allow: 3, deny: 0 });
+        assert!(decode_acl(&payload[..payload.len() - 1]).is_none());
+    }
+}
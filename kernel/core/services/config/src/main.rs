@@ -0,0 +1,230 @@
+/*
+ * Orion Operating System - Configuration Server
+ *
+ * Keeps the declarative system configuration (network interfaces, network
+ * shares, storage access lists; see schema.rs) in the VFS and applies it
+ * to the network, file system and storage servers, so that what an
+ * administrator set survives a reboot. Changes are transactional: a
+ * document is checked against the schema first, then applied all or
+ * nothing (apply.rs), and only recorded as a new version once every
+ * server took it. The last versions are kept (store.rs) and any of them
+ * can be applied again.
+ *
+ * At startup the current version is applied. A version that never saw a
+ * boot complete is replaced by the last known good one when it fails to
+ * apply or keeps the system from booting (manager.rs); init confirms the
+ * version once the boot finished.
+ *
+ * Reading the configuration needs CAP_READ, changing it CAP_ADMIN. Every
+ * change is audited.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+use orion_sys::{audit_emit, clock_get};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the configuration versions
+const HEAP_SIZE: usize = 2 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod apply;
+mod manager;
+mod protocol;
+mod schema;
+mod store;
+
+use apply::IpcServices;
+use manager::{BootOutcome, ConfigError, ConfigManager};
+use protocol::*;
+use store::{Store, VfsFiles};
+
+const CLOCK_ID_REALTIME: u32 = 1;
+
+/// Audit event types emitted by the configuration server (user range, see capabilities.c)
+const AUDIT_CONFIG_APPLY: u32 = 0x1401;
+const AUDIT_CONFIG_ROLLBACK: u32 = 0x1402;
+
+fn realtime_ns() -> u64 {
+    clock_get(CLOCK_ID_REALTIME).unwrap_or(0)
+}
+
+struct ConfigServer {
+    manager: ConfigManager<VfsFiles, IpcServices>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl ConfigServer {
+    fn new() -> Self {
+        let store = match Store::open(VfsFiles::connect()) {
+            Ok(store) => store,
+            Err(status) => panic!("config: unable to read the stored configuration ({})", status),
+        };
+        Self {
+            manager: ConfigManager::new(store, IpcServices::connect()),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn boot(&mut self) {
+        match self.manager.boot(realtime_ns()) {
+            Ok(BootOutcome::Empty) => {}
+            Ok(BootOutcome::Applied(version)) => {
+                let record = format!("config-boot version={}", version);
+                let _ = audit_emit(AUDIT_CONFIG_APPLY, record.as_bytes());
+            }
+            Ok(BootOutcome::RolledBack { failed, restored }) => {
+                let record = format!("config-boot-rollback failed={} restored={}", failed, restored);
+                let _ = audit_emit(AUDIT_CONFIG_ROLLBACK, record.as_bytes());
+            }
+            // The servers keep their defaults; an administrator can apply
+            // a version by hand
+            Err(error) => {
+                let record = format!("config-boot status={} error=\"{}\"", error.status(), error.message());
+                let _ = audit_emit(AUDIT_CONFIG_APPLY, record.as_bytes());
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        self.boot();
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    /// Status and payload of a change, the message on failure
+    fn change(result: Result<u32, ConfigError>, payload: &mut Vec<u8>) -> i32 {
+        match result {
+            Ok(version) => {
+                payload.extend_from_slice(&version.to_le_bytes());
+                STATUS_OK
+            }
+            Err(error) => {
+                put_string(payload, &error.message());
+                error.status()
+            }
+        }
+    }
+
+    fn history(&self) -> Vec<u8> {
+        let state = self.manager.state();
+        let mut payload = Vec::new();
+        for value in [state.current, state.known_good, state.boot_attempts, state.history.len() as u32] {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        for entry in state.history.iter() {
+            payload.extend_from_slice(&entry.version.to_le_bytes());
+            payload.extend_from_slice(&entry.origin.to_le_bytes());
+            payload.extend_from_slice(&entry.time.to_le_bytes());
+            put_string(&mut payload, &entry.comment);
+        }
+        payload
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let Some(request) = ConfigRequest::decode(&message.data) else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+            return;
+        };
+
+        let rights = match request {
+            ConfigRequest::Get { .. } | ConfigRequest::Validate { .. } | ConfigRequest::History => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let status = match request {
+            ConfigRequest::Get { version } => match self.manager.document(version) {
+                Ok(document) => {
+                    let current = self.manager.state().current;
+                    payload.extend_from_slice(&(if version == 0 { current } else { version }).to_le_bytes());
+                    payload.extend_from_slice(&(document.len() as u32).to_le_bytes());
+                    payload.extend_from_slice(&document);
+                    STATUS_OK
+                }
+                Err(status) => status,
+            },
+            ConfigRequest::Validate { document } => match self.manager.validate(&document) {
+                Ok(()) => STATUS_OK,
+                Err(error) => {
+                    put_string(&mut payload, &error.message());
+                    error.status()
+                }
+            },
+            ConfigRequest::Apply { comment, document } => {
+                let result = self.manager.apply(&document, &comment, realtime_ns());
+                let record = match &result {
+                    Ok(version) => format!("config-apply version={} sender={} comment=\"{}\"", version, message.sender, comment),
+                    Err(error) => format!("config-apply status={} sender={} error=\"{}\"", error.status(), message.sender, error.message()),
+                };
+                let _ = audit_emit(AUDIT_CONFIG_APPLY, record.as_bytes());
+                Self::change(result, &mut payload)
+            }
+            ConfigRequest::Rollback { version } => {
+                let result = self.manager.rollback(version, realtime_ns());
+                let record = match &result {
+                    Ok(new) => format!("config-rollback target={} version={} sender={}", version, new, message.sender),
+                    Err(error) => format!("config-rollback target={} status={} sender={}", version, error.status(), message.sender),
+                };
+                let _ = audit_emit(AUDIT_CONFIG_ROLLBACK, record.as_bytes());
+                Self::change(result, &mut payload)
+            }
+            ConfigRequest::Confirm => match self.manager.confirm() {
+                Ok(()) => STATUS_OK,
+                Err(error) => error.status(),
+            },
+            ConfigRequest::History => {
+                payload = self.history();
+                STATUS_OK
+            }
+        };
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = ConfigServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
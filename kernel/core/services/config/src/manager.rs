@@ -0,0 +1,327 @@
+/*
+ * Orion Operating System - Configuration Versions
+ *
+ * Ties the store to the services: a configuration becomes a new version
+ * only once every service took it, and the stored state always names the
+ * version the services run with.
+ *
+ * A version is known good once a boot that started with it completes
+ * (CONFIRM). Until then every boot counts an attempt before applying it;
+ * after MAX_BOOT_ATTEMPTS boots that never got that far, or as soon as
+ * applying it at boot fails, the known good configuration is applied as
+ * a new version instead, so that a change which breaks the boot (a share
+ * the boot waits on, a wrong MTU on the management link) undoes itself.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::apply::{execute, plan, ApplyError, Services};
+use crate::protocol::{STATUS_EBUSY, STATUS_EINVAL, STATUS_ENOENT};
+use crate::schema::{SchemaError, SystemConfig};
+use crate::store::{Files, Store, StoreState, MAX_HISTORY, ORIGIN_APPLY, ORIGIN_BOOT_ROLLBACK, ORIGIN_ROLLBACK};
+
+/// Boots an unconfirmed version gets before the known good one returns
+pub const MAX_BOOT_ATTEMPTS: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Schema(SchemaError),
+    Apply(ApplyError),
+    Status(i32),
+}
+
+impl From<i32> for ConfigError {
+    fn from(status: i32) -> Self {
+        ConfigError::Status(status)
+    }
+}
+
+impl ConfigError {
+    pub fn status(&self) -> i32 {
+        match self {
+            ConfigError::Schema(_) => STATUS_EINVAL,
+            ConfigError::Apply(error) => error.status,
+            ConfigError::Status(status) => *status,
+        }
+    }
+
+    /// What to tell the caller, empty when the status says it all
+    pub fn message(&self) -> String {
+        match self {
+            ConfigError::Schema(error) => error.message(),
+            ConfigError::Apply(error) if error.rolled_back => format!("{} failed ({})", error.step, error.status),
+            ConfigError::Apply(error) => format!("{} failed ({}), rollback incomplete", error.step, error.status),
+            ConfigError::Status(_) => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootOutcome {
+    /// Nothing was ever applied
+    Empty,
+    Applied(u32),
+    /// `failed` never completed a boot; `restored` is the known good
+    /// configuration applied again
+    RolledBack { failed: u32, restored: u32 },
+}
+
+pub struct ConfigManager<F: Files, S: Services> {
+    store: Store<F>,
+    services: S,
+    /// What the services currently run with
+    applied: SystemConfig,
+}
+
+impl<F: Files, S: Services> ConfigManager<F, S> {
+    /// Nothing is applied until boot
+    pub fn new(store: Store<F>, services: S) -> Self {
+        Self { store, services, applied: SystemConfig::default() }
+    }
+
+    pub fn state(&self) -> &StoreState {
+        self.store.state()
+    }
+
+    /// Canonical document of `version`; version 0 is the empty configuration
+    fn stored(&mut self, version: u32) -> Result<Vec<u8>, i32> {
+        if version == 0 {
+            return Ok(SystemConfig::default().to_json().into_bytes());
+        }
+        if !self.store.state().history.iter().any(|entry| entry.version == version) {
+            return Err(STATUS_ENOENT);
+        }
+        self.store.document(version)
+    }
+
+    fn load(&mut self, version: u32) -> Result<SystemConfig, ConfigError> {
+        SystemConfig::parse(&self.stored(version)?).map_err(ConfigError::Schema)
+    }
+
+    /// Document of a version in the history, the current one for 0
+    pub fn document(&mut self, version: u32) -> Result<Vec<u8>, i32> {
+        match version {
+            0 => self.stored(self.store.state().current),
+            version => self.stored(version),
+        }
+    }
+
+    pub fn validate(&self, document: &[u8]) -> Result<(), ConfigError> {
+        SystemConfig::parse(document).map(|_| ()).map_err(ConfigError::Schema)
+    }
+
+    pub fn apply(&mut self, document: &[u8], comment: &str, now: u64) -> Result<u32, ConfigError> {
+        let config = SystemConfig::parse(document).map_err(ConfigError::Schema)?;
+        self.install(config, ORIGIN_APPLY, comment, now)
+    }
+
+    /// Apply `version` again, the known good one for 0
+    pub fn rollback(&mut self, version: u32, now: u64) -> Result<u32, ConfigError> {
+        let target = match version {
+            0 => self.store.state().known_good,
+            version => version,
+        };
+        let config = self.load(target)?;
+        self.install(config, ORIGIN_ROLLBACK, &format!("rollback to version {}", target), now)
+    }
+
+    pub fn confirm(&mut self) -> Result<(), ConfigError> {
+        let state = self.store.state_mut();
+        state.known_good = state.current;
+        state.boot_attempts = 0;
+        self.store.save().map_err(ConfigError::Status)
+    }
+
+    /// Apply `config` to the services and record it as a new version
+    fn install(&mut self, config: SystemConfig, origin: u32, comment: &str, now: u64) -> Result<u32, ConfigError> {
+        let state = self.store.state();
+        let version = state.next_version;
+        // The known good document lives in the slot the version after
+        // MAX_HISTORY others would reuse
+        if origin != ORIGIN_BOOT_ROLLBACK && state.known_good != 0 && version - state.known_good >= MAX_HISTORY {
+            return Err(ConfigError::Status(STATUS_EBUSY));
+        }
+        self.store.write_document(version, config.to_json().as_bytes())?;
+        execute(&mut self.services, &plan(&self.applied, &config)).map_err(ConfigError::Apply)?;
+        let previous = core::mem::replace(&mut self.applied, config);
+
+        let saved = self.store.state().clone();
+        let state = self.store.state_mut();
+        state.current = version;
+        state.next_version = version + 1;
+        state.boot_attempts = 0;
+        if origin == ORIGIN_BOOT_ROLLBACK {
+            state.known_good = version;
+        }
+        self.store.record(version, origin, now, comment);
+        if let Err(status) = self.store.save() {
+            // The next boot would not find this version: go back to the
+            // one it will find
+            *self.store.state_mut() = saved;
+            let _ = execute(&mut self.services, &plan(&self.applied, &previous));
+            self.applied = previous;
+            return Err(ConfigError::Status(status));
+        }
+        Ok(version)
+    }
+
+    fn start(&mut self, version: u32) -> Result<(), ConfigError> {
+        let config = self.load(version)?;
+        execute(&mut self.services, &plan(&self.applied, &config)).map_err(ConfigError::Apply)?;
+        self.applied = config;
+        Ok(())
+    }
+
+    fn restore(&mut self, failed: u32, now: u64) -> Result<BootOutcome, ConfigError> {
+        let known_good = self.store.state().known_good;
+        let config = self.load(known_good)?;
+        let comment = format!("version {} never completed a boot", failed);
+        let restored = self.install(config, ORIGIN_BOOT_ROLLBACK, &comment, now)?;
+        Ok(BootOutcome::RolledBack { failed, restored })
+    }
+
+    /// Bring the services to the stored configuration at boot
+    pub fn boot(&mut self, now: u64) -> Result<BootOutcome, ConfigError> {
+        let state = self.store.state();
+        let (current, confirmed) = (state.current, state.current == state.known_good);
+        if current == 0 {
+            return Ok(BootOutcome::Empty);
+        }
+        if !confirmed {
+            if state.boot_attempts >= MAX_BOOT_ATTEMPTS {
+                return self.restore(current, now);
+            }
+            // Counted before applying, so a boot that hangs in the apply
+            // counts too
+            self.store.state_mut().boot_attempts += 1;
+            self.store.save()?;
+        }
+        match self.start(current) {
+            Ok(()) => Ok(BootOutcome::Applied(current)),
+            Err(_) if !confirmed => self.restore(current, now),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply::tests::FakeServices;
+    use crate::protocol::STATUS_EIO;
+    use crate::store::tests::MemoryFiles;
+
+    const FIRST: &[u8] = br#"{"interfaces": [{"name": "eth0", "up": true, "mtu": 1500}]}"#;
+    const JUMBO: &[u8] = br#"{"interfaces": [{"name": "eth0", "up": true, "mtu": 9000}],
+        "mounts": [{"path": "/mnt/media", "type": "nfs", "source": "nas:/media"}]}"#;
+
+    fn boot(files: &mut MemoryFiles, services: &mut FakeServices) -> Result<BootOutcome, ConfigError> {
+        let store = Store::open(files).unwrap();
+        ConfigManager::new(store, services).boot(0)
+    }
+
+    #[test]
+    fn test_configuration_survives_reboots() {
+        let mut files = MemoryFiles::default();
+        let mut services = FakeServices::with_interface("eth0");
+        assert_eq!(boot(&mut files, &mut services), Ok(BootOutcome::Empty));
+
+        let mut manager = ConfigManager::new(Store::open(&mut files).unwrap(), &mut services);
+        assert_eq!(manager.apply(FIRST, "initial", 10), Ok(1));
+        assert_eq!(manager.confirm(), Ok(()));
+        let error = manager.apply(br#"{"interfaces": [{"name": "eth0", "mtu": 1}]}"#, "", 20).unwrap_err();
+        assert_eq!(error.status(), STATUS_EINVAL);
+        assert_eq!(error.message(), "interfaces[0].mtu: must be an integer from 68 to 9216");
+        assert_eq!(manager.state().next_version, 2);
+        let canonical = manager.document(0).unwrap();
+        assert!(canonical.starts_with(b"{\"schema\":1,"));
+        drop(manager);
+
+        let mut services = FakeServices::with_interface("eth0");
+        assert_eq!(boot(&mut files, &mut services), Ok(BootOutcome::Applied(1)));
+        assert_eq!(services.interfaces["eth0"], (true, 1500));
+    }
+
+    #[test]
+    fn test_unconfirmed_versions_are_rolled_back() {
+        let mut files = MemoryFiles::default();
+        let mut services = FakeServices::with_interface("eth0");
+        let mut manager = ConfigManager::new(Store::open(&mut files).unwrap(), &mut services);
+        manager.apply(FIRST, "initial", 10).unwrap();
+        manager.confirm().unwrap();
+        assert_eq!(manager.apply(JUMBO, "jumbo frames", 20), Ok(2));
+        drop(manager);
+
+        // Two boots with version 2 never confirm it
+        for _ in 0..MAX_BOOT_ATTEMPTS {
+            let mut services = FakeServices::with_interface("eth0");
+            assert_eq!(boot(&mut files, &mut services), Ok(BootOutcome::Applied(2)));
+        }
+        let mut services = FakeServices::with_interface("eth0");
+        assert_eq!(boot(&mut files, &mut services), Ok(BootOutcome::RolledBack { failed: 2, restored: 3 }));
+        assert_eq!(services.interfaces["eth0"], (true, 1500));
+        assert!(services.mounts.is_empty());
+
+        let store = Store::open(&mut files).unwrap();
+        let state = store.state();
+        assert_eq!((state.current, state.known_good, state.boot_attempts), (3, 3, 0));
+        assert_eq!(state.history.last().unwrap().origin, ORIGIN_BOOT_ROLLBACK);
+    }
+
+    #[test]
+    fn test_failed_applies_change_nothing() {
+        let mut files = MemoryFiles::default();
+        let mut services = FakeServices::with_interface("eth0");
+        services.fail.push(String::from("mount /mnt/media"));
+        let mut manager = ConfigManager::new(Store::open(&mut files).unwrap(), &mut services);
+        manager.apply(FIRST, "initial", 10).unwrap();
+        manager.confirm().unwrap();
+
+        let error = manager.apply(JUMBO, "jumbo frames", 20).unwrap_err();
+        assert_eq!(error.status(), STATUS_EIO);
+        assert_eq!(error.message(), "mount /mnt/media failed (-5)");
+        assert_eq!(manager.state().current, 1);
+        assert_eq!(manager.rollback(7, 30), Err(ConfigError::Status(STATUS_ENOENT)));
+        drop(manager);
+        assert_eq!(services.interfaces["eth0"], (true, 1500));
+
+        let mut working = FakeServices::with_interface("eth0");
+        let mut manager = ConfigManager::new(Store::open(&mut files).unwrap(), &mut working);
+        assert_eq!(manager.boot(40), Ok(BootOutcome::Applied(1)));
+        assert_eq!(manager.apply(JUMBO, "jumbo frames", 50), Ok(2));
+        assert_eq!(manager.rollback(0, 60), Ok(3));
+        assert_eq!(manager.apply(JUMBO, "jumbo frames again", 70), Ok(4));
+        drop(manager);
+        assert_eq!(working.interfaces["eth0"], (true, 9000));
+
+        // Failing at boot sends an unconfirmed version back right away
+        let mut services = FakeServices::with_interface("eth0");
+        services.fail.push(String::from("mount /mnt/media"));
+        assert_eq!(boot(&mut files, &mut services), Ok(BootOutcome::RolledBack { failed: 4, restored: 5 }));
+        assert_eq!(services.interfaces["eth0"], (true, 1500));
+        assert!(services.mounts.is_empty());
+    }
+
+    #[test]
+    fn test_the_known_good_version_is_kept() {
+        let mut files = MemoryFiles::default();
+        let mut services = FakeServices::with_interface("eth0");
+        let mut manager = ConfigManager::new(Store::open(&mut files).unwrap(), &mut services);
+        manager.apply(FIRST, "initial", 0).unwrap();
+        manager.confirm().unwrap();
+        for version in 2..=MAX_HISTORY {
+            let document = alloc::format!(r#"{{"interfaces": [{{"name": "eth0", "mtu": {}}}]}}"#, 1000 + version);
+            assert_eq!(manager.apply(document.as_bytes(), "", 0), Ok(version));
+        }
+        assert_eq!(manager.apply(FIRST, "", 0), Err(ConfigError::Status(STATUS_EBUSY)));
+        assert_eq!(SystemConfig::parse(&manager.document(1).unwrap()).unwrap().interfaces[0].mtu, Some(1500));
+    }
+}
@@ -0,0 +1,133 @@
+/*
+ * Orion Operating System - Configuration Server Protocol
+ *
+ * IPC requests of the configuration server. All fields are little-endian;
+ * every message starts with a 32-bit opcode and every reply starts with a
+ * 32-bit signed status (0 or a negative errno).
+ *
+ *   GET       version:u32        -> version:u32 document
+ *   VALIDATE  document           -> (empty)
+ *   APPLY     comment document   -> version:u32
+ *   ROLLBACK  version:u32        -> version:u32
+ *   CONFIRM   (none)             -> (empty)
+ *   HISTORY   (none)             -> current:u32 known_good:u32
+ *                                   boot_attempts:u32 count:u32 records
+ *
+ * GET returns the canonical document of a version still in the history,
+ * or of the current one for version 0. VALIDATE and APPLY take a JSON
+ * document (see schema.rs); when it is refused, or a step of the apply
+ * fails, the reply carries a message string naming the field or the step
+ * after the status. ROLLBACK applies an earlier version again as a new
+ * version, the known good one for version 0. CONFIRM marks the current
+ * version known good: init sends it once a boot completes.
+ *
+ * HISTORY records are version:u32 origin:u32 time:u64 comment, oldest
+ * first. Strings and documents are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_GET: u32 = 1;
+pub const OP_VALIDATE: u32 = 2;
+pub const OP_APPLY: u32 = 3;
+pub const OP_ROLLBACK: u32 = 4;
+pub const OP_CONFIRM: u32 = 5;
+pub const OP_HISTORY: u32 = 6;
+
+/// Largest document accepted, so that GET replies fit one IPC message
+pub const MAX_DOCUMENT: usize = 60 * 1024;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ESTALE: i32 = -116;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigRequest {
+    Get { version: u32 },
+    Validate { document: Vec<u8> },
+    Apply { comment: String, document: Vec<u8> },
+    Rollback { version: u32 },
+    Confirm,
+    History,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decode a `len, bytes` field and return it with the offset after it
+fn read_bytes(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let end = offset.checked_add(4 + len)?;
+    Some((data.get(offset + 4..end)?, end))
+}
+
+fn read_document(data: &[u8], offset: usize) -> Option<Vec<u8>> {
+    let (document, _) = read_bytes(data, offset)?;
+    (document.len() <= MAX_DOCUMENT).then(|| document.to_vec())
+}
+
+pub fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+impl ConfigRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_GET => Some(ConfigRequest::Get { version: read_u32(data, 4)? }),
+            OP_VALIDATE => Some(ConfigRequest::Validate { document: read_document(data, 4)? }),
+            OP_APPLY => {
+                let (comment, next) = read_bytes(data, 4)?;
+                let comment = String::from(core::str::from_utf8(comment).ok()?);
+                Some(ConfigRequest::Apply { comment, document: read_document(data, next)? })
+            }
+            OP_ROLLBACK => Some(ConfigRequest::Rollback { version: read_u32(data, 4)? }),
+            OP_CONFIRM => Some(ConfigRequest::Confirm),
+            OP_HISTORY => Some(ConfigRequest::History),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut data = OP_APPLY.to_le_bytes().to_vec();
+        put_string(&mut data, "jumbo frames");
+        put_string(&mut data, "{}");
+        let expected = ConfigRequest::Apply { comment: String::from("jumbo frames"), document: b"{}".to_vec() };
+        assert_eq!(ConfigRequest::decode(&data), Some(expected));
+        assert_eq!(ConfigRequest::decode(&data[..data.len() - 1]), None);
+
+        let mut data = OP_ROLLBACK.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(ConfigRequest::decode(&data), Some(ConfigRequest::Rollback { version: 0 }));
+        assert_eq!(ConfigRequest::decode(&OP_CONFIRM.to_le_bytes()), Some(ConfigRequest::Confirm));
+
+        let mut data = OP_VALIDATE.to_le_bytes().to_vec();
+        data.extend_from_slice(&((MAX_DOCUMENT + 1) as u32).to_le_bytes());
+        data.resize(8 + MAX_DOCUMENT + 1, b' ');
+        assert_eq!(ConfigRequest::decode(&data), None);
+        assert_eq!(ConfigRequest::decode(&99u32.to_le_bytes()), None);
+    }
+}
@@ -0,0 +1,409 @@
+/*
+ * Orion Operating System - Configuration Schema
+ *
+ * The declarative system configuration: a JSON document listing the
+ * network interfaces to bring up and their MTU, the network shares to
+ * mount, and the access lists of storage pools and volumes.
+ *
+ *   {
+ *     "schema": 1,
+ *     "interfaces": [{"name": "eth0", "up": true, "mtu": 9000}],
+ *     "mounts": [{"path": "/mnt/backup", "type": "nfs",
+ *                 "source": "nas:/backup", "options": "vers=4"}],
+ *     "access": [{"target": "pool", "name": "vg0", "entries": [
+ *                 {"principal": "process", "id": 42,
+ *                  "allow": ["read", "write"], "deny": []}]}]
+ *   }
+ *
+ * Every section is optional. Parsing is strict: unknown members, values
+ * of the wrong type, limits the servers would refuse and duplicates are
+ * reported with the path of the offending field, so that a document is
+ * rejected before anything is applied rather than half way through.
+ * Documents are stored in the canonical form written by to_json.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_http::json::array;
+use orion_http::{JsonError, ObjectWriter, Value};
+
+/// Version of the document layout this service understands
+pub const SCHEMA_VERSION: u64 = 1;
+
+// Limits of the servers the configuration is applied to
+pub const IFACE_NAME_SIZE: usize = 32;
+pub const IFACE_MIN_MTU: u64 = 68;
+pub const IFACE_MAX_MTU: u64 = 9216;
+pub const MAX_PATH: usize = 4096;
+pub const ACL_MAX_ENTRIES: usize = 64;
+
+/// Most items of each section
+pub const MAX_ITEMS: usize = 64;
+
+// Access list targets and principals (drivers/block/src/lvm.rs)
+pub const ACL_TARGET_POOL: u32 = 0;
+pub const ACL_TARGET_VOLUME: u32 = 1;
+pub const ACL_PRINCIPAL_EVERYONE: u32 = 0;
+pub const ACL_PRINCIPAL_CAPABILITY: u32 = 1;
+pub const ACL_PRINCIPAL_PROCESS: u32 = 2;
+
+const ACL_TARGETS: [(u32, &str); 2] = [(ACL_TARGET_POOL, "pool"), (ACL_TARGET_VOLUME, "volume")];
+const ACL_PRINCIPALS: [(u32, &str); 3] = [
+    (ACL_PRINCIPAL_EVERYONE, "everyone"),
+    (ACL_PRINCIPAL_CAPABILITY, "capability"),
+    (ACL_PRINCIPAL_PROCESS, "process"),
+];
+const ACL_RIGHTS: [(u32, &str); 4] = [(1 << 0, "read"), (1 << 1, "write"), (1 << 2, "snapshot"), (1 << 3, "admin")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceConfig {
+    pub name: String,
+    /// None leaves the setting to whatever brought the interface up
+    pub up: Option<bool>,
+    pub mtu: Option<u32>,
+}
+
+/// A network share; NFS is the only kind the file system server mounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountConfig {
+    pub path: String,
+    pub source: String,
+    pub options: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessEntry {
+    pub principal: u32,
+    /// Capability or process id, 0 for everyone
+    pub id: u64,
+    pub allow: u32,
+    pub deny: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessConfig {
+    pub target: u32,
+    pub name: String,
+    pub entries: Vec<AccessEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemConfig {
+    pub interfaces: Vec<InterfaceConfig>,
+    pub mounts: Vec<MountConfig>,
+    pub access: Vec<AccessConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// Not a JSON document, with the byte offset of the problem
+    Syntax(usize),
+    Invalid { field: String, reason: &'static str },
+}
+
+impl SchemaError {
+    pub fn message(&self) -> String {
+        match self {
+            SchemaError::Syntax(offset) => format!("malformed JSON at byte {}", offset),
+            SchemaError::Invalid { field, reason } => format!("{}: {}", field, reason),
+        }
+    }
+}
+
+fn invalid<T>(field: &str, reason: &'static str) -> Result<T, SchemaError> {
+    Err(SchemaError::Invalid { field: String::from(field), reason })
+}
+
+/// Check that `value` is an object whose members are all in `allowed`
+fn object(value: &Value, field: &str, allowed: &[&str]) -> Result<(), SchemaError> {
+    let Value::Object(members) = value else {
+        return invalid(field, "expected an object");
+    };
+    for (name, _) in members {
+        if !allowed.contains(&name.as_str()) {
+            return invalid(&format!("{}.{}", field, name), "unknown member");
+        }
+    }
+    Ok(())
+}
+
+fn section<'a>(document: &'a Value, name: &str) -> Result<&'a [Value], SchemaError> {
+    match document.get(name) {
+        None => Ok(&[]),
+        Some(value) => {
+            let items = value.as_array().map_or_else(|| invalid(name, "expected an array"), Ok)?;
+            if items.len() > MAX_ITEMS {
+                return invalid(name, "too many items");
+            }
+            Ok(items)
+        }
+    }
+}
+
+fn string(value: &Value, field: &str, name: &str) -> Result<String, SchemaError> {
+    let field = format!("{}.{}", field, name);
+    match value.get(name).map(Value::as_str) {
+        Some(Some(text)) => Ok(String::from(text)),
+        Some(None) => invalid(&field, "expected a string"),
+        None => invalid(&field, "missing"),
+    }
+}
+
+fn lookup(table: &[(u32, &str)], name: &str) -> Option<u32> {
+    table.iter().find(|(_, entry)| *entry == name).map(|(value, _)| *value)
+}
+
+fn name_of(table: &[(u32, &'static str)], value: u32) -> &'static str {
+    table.iter().find(|(entry, _)| *entry == value).map_or("", |(_, name)| name)
+}
+
+/// Absolute path without empty, "." or ".." components
+fn valid_path(path: &str) -> bool {
+    path.len() <= MAX_PATH
+        && path.starts_with('/')
+        && (path == "/" || path[1..].split('/').all(|part| !part.is_empty() && part != "." && part != ".."))
+}
+
+fn parse_interface(value: &Value, field: &str) -> Result<InterfaceConfig, SchemaError> {
+    object(value, field, &["name", "up", "mtu"])?;
+    let name = string(value, field, "name")?;
+    if name.is_empty() || name.len() >= IFACE_NAME_SIZE {
+        return invalid(&format!("{}.name", field), "must be 1 to 31 bytes");
+    }
+    let up = match value.get("up") {
+        None => None,
+        Some(up) => Some(up.as_bool().map_or_else(|| invalid(&format!("{}.up", field), "expected a boolean"), Ok)?),
+    };
+    let mtu = match value.get("mtu") {
+        None => None,
+        Some(mtu) => match mtu.as_u64() {
+            Some(mtu) if (IFACE_MIN_MTU..=IFACE_MAX_MTU).contains(&mtu) => Some(mtu as u32),
+            _ => return invalid(&format!("{}.mtu", field), "must be an integer from 68 to 9216"),
+        },
+    };
+    Ok(InterfaceConfig { name, up, mtu })
+}
+
+fn parse_mount(value: &Value, field: &str) -> Result<MountConfig, SchemaError> {
+    object(value, field, &["path", "type", "source", "options"])?;
+    let path = string(value, field, "path")?;
+    if !valid_path(&path) || path == "/" {
+        return invalid(&format!("{}.path", field), "must be an absolute path below /");
+    }
+    if string(value, field, "type")? != "nfs" {
+        return invalid(&format!("{}.type", field), "only nfs shares can be mounted");
+    }
+    let source = string(value, field, "source")?;
+    match source.split_once(':') {
+        Some((host, export)) if !host.is_empty() && export.starts_with('/') => {}
+        _ => return invalid(&format!("{}.source", field), "expected host:/export"),
+    }
+    let options = match value.get("options") {
+        None => String::new(),
+        Some(_) => string(value, field, "options")?,
+    };
+    Ok(MountConfig { path, source, options })
+}
+
+fn parse_rights(value: &Value, field: &str, name: &str) -> Result<u32, SchemaError> {
+    let field = format!("{}.{}", field, name);
+    let Some(value) = value.get(name) else {
+        return Ok(0);
+    };
+    let Some(items) = value.as_array() else {
+        return invalid(&field, "expected an array of rights");
+    };
+    let mut rights = 0;
+    for item in items {
+        match item.as_str().and_then(|right| lookup(&ACL_RIGHTS, right)) {
+            Some(right) => rights |= right,
+            None => return invalid(&field, "unknown right"),
+        }
+    }
+    Ok(rights)
+}
+
+fn parse_entry(value: &Value, field: &str) -> Result<AccessEntry, SchemaError> {
+    object(value, field, &["principal", "id", "allow", "deny"])?;
+    let Some(principal) = lookup(&ACL_PRINCIPALS, &string(value, field, "principal")?) else {
+        return invalid(&format!("{}.principal", field), "expected everyone, capability or process");
+    };
+    let id = match (principal, value.get("id").map(Value::as_u64)) {
+        (ACL_PRINCIPAL_EVERYONE, None) => 0,
+        (ACL_PRINCIPAL_EVERYONE, Some(_)) => return invalid(&format!("{}.id", field), "everyone takes no id"),
+        (_, Some(Some(id))) if id != 0 => id,
+        _ => return invalid(&format!("{}.id", field), "expected a non-zero id"),
+    };
+    let allow = parse_rights(value, field, "allow")?;
+    let deny = parse_rights(value, field, "deny")?;
+    Ok(AccessEntry { principal, id, allow, deny })
+}
+
+fn parse_access(value: &Value, field: &str) -> Result<AccessConfig, SchemaError> {
+    object(value, field, &["target", "name", "entries"])?;
+    let Some(target) = lookup(&ACL_TARGETS, &string(value, field, "target")?) else {
+        return invalid(&format!("{}.target", field), "expected pool or volume");
+    };
+    let name = string(value, field, "name")?;
+    if name.is_empty() {
+        return invalid(&format!("{}.name", field), "missing");
+    }
+    let entries_field = format!("{}.entries", field);
+    let Some(items) = value.get("entries").and_then(Value::as_array) else {
+        return invalid(&entries_field, "expected an array");
+    };
+    if items.len() > ACL_MAX_ENTRIES {
+        return invalid(&entries_field, "too many entries");
+    }
+    let mut entries = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        entries.push(parse_entry(item, &format!("{}[{}]", entries_field, index))?);
+    }
+    Ok(AccessConfig { target, name, entries })
+}
+
+/// Parse every item of a section, refusing two items with the same key
+fn parse_section<T, K: PartialEq>(
+    document: &Value,
+    name: &str,
+    parse: impl Fn(&Value, &str) -> Result<T, SchemaError>,
+    key: impl Fn(&T) -> K,
+) -> Result<Vec<T>, SchemaError> {
+    let mut items: Vec<T> = Vec::new();
+    for (index, value) in section(document, name)?.iter().enumerate() {
+        let field = format!("{}[{}]", name, index);
+        let item = parse(value, &field)?;
+        if items.iter().any(|other| key(other) == key(&item)) {
+            return invalid(&field, "duplicate");
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
+
+impl SystemConfig {
+    pub fn parse(text: &[u8]) -> Result<Self, SchemaError> {
+        let document = Value::parse(text).map_err(|error| match error {
+            JsonError::Syntax(offset) => SchemaError::Syntax(offset),
+            JsonError::TooDeep => SchemaError::Invalid { field: String::from("document"), reason: "nested too deeply" },
+        })?;
+        object(&document, "document", &["schema", "interfaces", "mounts", "access"])?;
+        match document.get("schema").map(Value::as_u64) {
+            None | Some(Some(SCHEMA_VERSION)) => {}
+            Some(_) => return invalid("schema", "unsupported version"),
+        }
+
+        Ok(Self {
+            interfaces: parse_section(&document, "interfaces", parse_interface, |item| item.name.clone())?,
+            mounts: parse_section(&document, "mounts", parse_mount, |item| item.path.clone())?,
+            access: parse_section(&document, "access", parse_access, |item| (item.target, item.name.clone()))?,
+        })
+    }
+
+    /// Canonical document: every member written, in schema order
+    pub fn to_json(&self) -> String {
+        let interfaces = array(self.interfaces.iter().map(|interface| {
+            let mut writer = ObjectWriter::new().string("name", &interface.name);
+            if let Some(up) = interface.up {
+                writer = writer.boolean("up", up);
+            }
+            if let Some(mtu) = interface.mtu {
+                writer = writer.unsigned("mtu", mtu as u64);
+            }
+            writer.finish()
+        }));
+        let mounts = array(self.mounts.iter().map(|mount| {
+            ObjectWriter::new()
+                .string("path", &mount.path)
+                .string("type", "nfs")
+                .string("source", &mount.source)
+                .string("options", &mount.options)
+                .finish()
+        }));
+        let rights = |mask: u32| {
+            array(ACL_RIGHTS.iter().filter(|(right, _)| mask & right != 0).map(|(_, name)| format!("\"{}\"", name)))
+        };
+        let access = array(self.access.iter().map(|access| {
+            let entries = array(access.entries.iter().map(|entry| {
+                let mut writer = ObjectWriter::new().string("principal", name_of(&ACL_PRINCIPALS, entry.principal));
+                if entry.principal != ACL_PRINCIPAL_EVERYONE {
+                    writer = writer.unsigned("id", entry.id);
+                }
+                writer.raw("allow", &rights(entry.allow)).raw("deny", &rights(entry.deny)).finish()
+            }));
+            ObjectWriter::new()
+                .string("target", name_of(&ACL_TARGETS, access.target))
+                .string("name", &access.name)
+                .raw("entries", &entries)
+                .finish()
+        }));
+        ObjectWriter::new()
+            .unsigned("schema", SCHEMA_VERSION)
+            .raw("interfaces", &interfaces)
+            .raw("mounts", &mounts)
+            .raw("access", &access)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"{
+        "schema": 1,
+        "interfaces": [{"name": "eth0", "up": true, "mtu": 9000}, {"name": "eth1", "up": false}],
+        "mounts": [{"path": "/mnt/backup", "type": "nfs", "source": "nas:/backup"}],
+        "access": [{"target": "volume", "name": "home", "entries": [
+            {"principal": "everyone", "allow": ["read"]},
+            {"principal": "process", "id": 42, "allow": ["read", "write"], "deny": ["admin"]}]}]
+    }"#;
+
+    fn error_field(text: &str) -> String {
+        match SystemConfig::parse(text.as_bytes()) {
+            Err(SchemaError::Invalid { field, .. }) => field,
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parses_and_round_trips() {
+        let config = SystemConfig::parse(DOCUMENT.as_bytes()).unwrap();
+        assert_eq!(config.interfaces[0], InterfaceConfig { name: String::from("eth0"), up: Some(true), mtu: Some(9000) });
+        assert_eq!(config.interfaces[1].mtu, None);
+        assert_eq!(config.mounts[0].options, "");
+        assert_eq!(config.access[0].target, ACL_TARGET_VOLUME);
+        assert_eq!(config.access[0].entries[1], AccessEntry { principal: ACL_PRINCIPAL_PROCESS, id: 42, allow: 3, deny: 8 });
+
+        let canonical = config.to_json();
+        assert_eq!(SystemConfig::parse(canonical.as_bytes()).unwrap(), config);
+        assert_eq!(SystemConfig::parse(b"{}").unwrap(), SystemConfig::default());
+    }
+
+    #[test]
+    fn test_reports_the_offending_field() {
+        assert_eq!(SystemConfig::parse(b"{\"interfaces\": ["), Err(SchemaError::Syntax(16)));
+        assert_eq!(error_field(r#"{"schema": 2}"#), "schema");
+        assert_eq!(error_field(r#"{"network": []}"#), "document.network");
+        assert_eq!(error_field(r#"{"interfaces": [{"name": "eth0", "mtu": 20}]}"#), "interfaces[0].mtu");
+        assert_eq!(error_field(r#"{"interfaces": [{"name": "eth0"}, {"name": "eth0"}]}"#), "interfaces[1]");
+        assert_eq!(error_field(r#"{"mounts": [{"path": "/mnt/../etc", "type": "nfs", "source": "a:/b"}]}"#), "mounts[0].path");
+        assert_eq!(error_field(r#"{"mounts": [{"path": "/mnt", "type": "nfs", "source": "nas"}]}"#), "mounts[0].source");
+        assert_eq!(
+            error_field(r#"{"access": [{"target": "pool", "name": "vg0", "entries": [{"principal": "process"}]}]}"#),
+            "access[0].entries[0].id"
+        );
+        assert_eq!(
+            error_field(r#"{"access": [{"target": "pool", "name": "vg0", "entries": [{"principal": "everyone", "allow": ["all"]}]}]}"#),
+            "access[0].entries[0].allow"
+        );
+    }
+}
@@ -0,0 +1,430 @@
+/*
+ * Orion Operating System - Configuration Store
+ *
+ * Persistent state of the configuration service, kept as files of the
+ * VFS under CONFIG_DIR (created by the installer):
+ *
+ *   state.0, state.1   which version is current, which one is known
+ *                      good, boot attempts and the history
+ *   version.<n>        document of version v, in slot v % MAX_HISTORY
+ *
+ * The direct file requests of the file system server cannot rename, so
+ * state updates alternate between two files: each write goes to the slot
+ * not holding the latest state and carries a sequence number, and the
+ * valid file with the highest sequence wins when the store is opened. A
+ * write torn by a crash fails its checksum and the previous state stands.
+ * Every file is one record: a header with the magic, the sequence, the
+ * body length and an FNV-1a checksum of the body, so stale bytes after a
+ * shorter rewrite are ignored.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+use crate::protocol::{STATUS_EIO, STATUS_ENOENT, STATUS_OK};
+
+pub const CONFIG_DIR: &str = "/etc/orion/config";
+
+/// Versions kept; older documents are overwritten by newer ones
+pub const MAX_HISTORY: u32 = 16;
+
+/// Longest comment recorded with a version
+pub const MAX_COMMENT: usize = 256;
+
+const RECORD_MAGIC: &[u8; 4] = b"OCFG";
+const RECORD_HEADER_SIZE: usize = 20;
+
+// File system direct file requests (see services/fs/src/files.rs)
+const FS_OP_OPEN: u32 = 0x41;
+const FS_OP_READ_AT: u32 = 0x42;
+const FS_OP_WRITE_AT: u32 = 0x43;
+const FS_OP_SYNC: u32 = 0x44;
+const FS_OP_CLOSE: u32 = 0x45;
+const FS_OPEN_READ: u32 = 0o1;
+const FS_OPEN_WRITE: u32 = 0o2;
+const FS_OPEN_CREATE: u32 = 0o10;
+const FS_MAX_TRANSFER: usize = 64 * 1024;
+
+// How a version came to be
+pub const ORIGIN_APPLY: u32 = 1;
+pub const ORIGIN_ROLLBACK: u32 = 2;
+/// Restored at boot because the version before it was never confirmed
+pub const ORIGIN_BOOT_ROLLBACK: u32 = 3;
+/// Written by the installer along with the rest of the data partition
+pub const ORIGIN_INSTALL: u32 = 4;
+
+/// Whole-file access to the VFS
+pub trait Files {
+    /// Contents of `path`, None when it does not exist
+    fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>, i32>;
+    /// Write `data` at the start of `path`, creating it, and sync it
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<(), i32>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub version: u32,
+    pub origin: u32,
+    /// Realtime clock when the version was applied, in nanoseconds
+    pub time: u64,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreState {
+    /// Version the services run with, 0 before the first apply
+    pub current: u32,
+    /// Last version confirmed by a completed boot, 0 for the empty
+    /// configuration
+    pub known_good: u32,
+    /// Boots started with `current` while it was not known good
+    pub boot_attempts: u32,
+    pub next_version: u32,
+    /// Oldest first, at most MAX_HISTORY entries
+    pub history: Vec<HistoryEntry>,
+}
+
+impl Default for StoreState {
+    fn default() -> Self {
+        Self { current: 0, known_good: 0, boot_attempts: 0, next_version: 1, history: Vec::new() }
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+pub fn encode_record(sequence: u64, body: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + body.len());
+    record.extend_from_slice(RECORD_MAGIC);
+    record.extend_from_slice(&sequence.to_le_bytes());
+    record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    record.extend_from_slice(&fnv1a(body).to_le_bytes());
+    record.extend_from_slice(body);
+    record
+}
+
+/// Sequence and body of an intact record
+pub fn decode_record(data: &[u8]) -> Option<(u64, &[u8])> {
+    if data.get(..4)? != RECORD_MAGIC {
+        return None;
+    }
+    let sequence = read_u64(data, 4)?;
+    let length = read_u32(data, 12)? as usize;
+    let body = data.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE.checked_add(length)?)?;
+    (fnv1a(body) == read_u32(data, 16)?).then_some((sequence, body))
+}
+
+impl StoreState {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for value in [self.current, self.known_good, self.boot_attempts, self.next_version] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.history.len() as u32).to_le_bytes());
+        for entry in self.history.iter() {
+            out.extend_from_slice(&entry.version.to_le_bytes());
+            out.extend_from_slice(&entry.origin.to_le_bytes());
+            out.extend_from_slice(&entry.time.to_le_bytes());
+            put_string(&mut out, &entry.comment);
+        }
+        out
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut state = StoreState {
+            current: read_u32(data, 0)?,
+            known_good: read_u32(data, 4)?,
+            boot_attempts: read_u32(data, 8)?,
+            next_version: read_u32(data, 12)?,
+            history: Vec::new(),
+        };
+        let count = read_u32(data, 16)?;
+        let mut offset = 20;
+        for _ in 0..count.min(MAX_HISTORY) {
+            let length = read_u32(data, offset + 16)? as usize;
+            let comment = data.get(offset + 20..offset + 20 + length)?;
+            state.history.push(HistoryEntry {
+                version: read_u32(data, offset)?,
+                origin: read_u32(data, offset + 4)?,
+                time: read_u64(data, offset + 8)?,
+                comment: String::from(core::str::from_utf8(comment).ok()?),
+            });
+            offset += 20 + length;
+        }
+        Some(state)
+    }
+}
+
+pub struct Store<F: Files> {
+    files: F,
+    state: StoreState,
+    /// Sequence of the state record last written or read
+    sequence: u64,
+}
+
+fn state_path(slot: u64) -> String {
+    format!("{}/state.{}", CONFIG_DIR, slot)
+}
+
+fn version_path(version: u32) -> String {
+    format!("{}/version.{}", CONFIG_DIR, version % MAX_HISTORY)
+}
+
+impl<F: Files> Store<F> {
+    /// Load the latest intact state, or start empty on a fresh system
+    pub fn open(mut files: F) -> Result<Self, i32> {
+        let mut latest: Option<(u64, StoreState)> = None;
+        for slot in 0..2 {
+            let Some(data) = files.read(&state_path(slot))? else {
+                continue;
+            };
+            let Some((sequence, body)) = decode_record(&data) else {
+                continue;
+            };
+            if latest.as_ref().is_some_and(|(newest, _)| *newest >= sequence) {
+                continue;
+            }
+            if let Some(state) = StoreState::decode(body) {
+                latest = Some((sequence, state));
+            }
+        }
+        let (sequence, state) = latest.unwrap_or_default();
+        Ok(Self { files, state, sequence })
+    }
+
+    pub fn state(&self) -> &StoreState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut StoreState {
+        &mut self.state
+    }
+
+    /// Persist the state into the slot the previous write did not use
+    pub fn save(&mut self) -> Result<(), i32> {
+        let sequence = self.sequence + 1;
+        let record = encode_record(sequence, &self.state.encode());
+        self.files.write(&state_path(sequence % 2), &record)?;
+        self.sequence = sequence;
+        Ok(())
+    }
+
+    /// Append a history entry, dropping the oldest beyond MAX_HISTORY
+    pub fn record(&mut self, version: u32, origin: u32, time: u64, comment: &str) {
+        let history = &mut self.state.history;
+        if history.len() >= MAX_HISTORY as usize {
+            history.remove(0);
+        }
+        let mut end = comment.len().min(MAX_COMMENT);
+        while !comment.is_char_boundary(end) {
+            end -= 1;
+        }
+        history.push(HistoryEntry { version, origin, time, comment: String::from(&comment[..end]) });
+    }
+
+    pub fn write_document(&mut self, version: u32, document: &[u8]) -> Result<(), i32> {
+        self.files.write(&version_path(version), &encode_record(version as u64, document))
+    }
+
+    /// Document of `version`, ENOENT once its slot went to a newer version
+    pub fn document(&mut self, version: u32) -> Result<Vec<u8>, i32> {
+        let data = self.files.read(&version_path(version))?.ok_or(STATUS_ENOENT)?;
+        match decode_record(&data) {
+            Some((sequence, body)) if sequence == version as u64 => Ok(body.to_vec()),
+            Some(_) => Err(STATUS_ENOENT),
+            None => Err(STATUS_EIO),
+        }
+    }
+}
+
+/// Files reached through the direct file requests of the file system server
+pub struct VfsFiles {
+    channel: IpcChannel,
+}
+
+impl VfsFiles {
+    pub fn connect() -> Self {
+        Self { channel: IpcChannel::connect("fs") }
+    }
+
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.channel.call(request).map_err(|_| STATUS_EIO)?;
+        if response.len() < 4 {
+            return Err(STATUS_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn open(&mut self, path: &str, flags: u32) -> Result<(u32, u64), i32> {
+        let mut request = FS_OP_OPEN.to_le_bytes().to_vec();
+        request.extend_from_slice(&flags.to_le_bytes());
+        put_string(&mut request, path);
+        let payload = self.call(&request)?;
+        Ok((read_u32(&payload, 0).ok_or(STATUS_EIO)?, read_u64(&payload, 4).ok_or(STATUS_EIO)?))
+    }
+
+    fn handle_request(opcode: u32, handle: u32) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        request.extend_from_slice(&handle.to_le_bytes());
+        request
+    }
+
+    fn read_all(&mut self, handle: u32, size: u64) -> Result<Vec<u8>, i32> {
+        let mut data = Vec::with_capacity(size as usize);
+        while (data.len() as u64) < size {
+            let mut request = Self::handle_request(FS_OP_READ_AT, handle);
+            request.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            request.extend_from_slice(&(FS_MAX_TRANSFER as u32).to_le_bytes());
+            let chunk = self.call(&request)?;
+            if chunk.is_empty() {
+                break;
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
+    fn write_all(&mut self, handle: u32, data: &[u8]) -> Result<(), i32> {
+        for (index, chunk) in data.chunks(FS_MAX_TRANSFER).enumerate() {
+            let mut request = Self::handle_request(FS_OP_WRITE_AT, handle);
+            request.extend_from_slice(&((index * FS_MAX_TRANSFER) as u64).to_le_bytes());
+            request.extend_from_slice(chunk);
+            if read_u32(&self.call(&request)?, 0) != Some(chunk.len() as u32) {
+                return Err(STATUS_EIO);
+            }
+        }
+        self.call(&Self::handle_request(FS_OP_SYNC, handle)).map(|_| ())
+    }
+}
+
+impl Files for VfsFiles {
+    fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>, i32> {
+        let (handle, size) = match self.open(path, FS_OPEN_READ) {
+            Ok(opened) => opened,
+            Err(STATUS_ENOENT) => return Ok(None),
+            Err(status) => return Err(status),
+        };
+        let result = self.read_all(handle, size);
+        let _ = self.call(&Self::handle_request(FS_OP_CLOSE, handle));
+        result.map(Some)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<(), i32> {
+        let (handle, _) = self.open(path, FS_OPEN_READ | FS_OPEN_WRITE | FS_OPEN_CREATE)?;
+        let result = self.write_all(handle, data);
+        let _ = self.call(&Self::handle_request(FS_OP_CLOSE, handle));
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    /// Files kept in memory; writes overwrite in place like the VFS does
+    #[derive(Default)]
+    pub struct MemoryFiles {
+        pub files: BTreeMap<String, Vec<u8>>,
+    }
+
+    impl Files for &mut MemoryFiles {
+        fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>, i32> {
+            Ok(self.files.get(path).cloned())
+        }
+
+        fn write(&mut self, path: &str, data: &[u8]) -> Result<(), i32> {
+            let file = self.files.entry(String::from(path)).or_default();
+            if file.len() < data.len() {
+                file.resize(data.len(), 0);
+            }
+            file[..data.len()].copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_records_reject_damage() {
+        let record = encode_record(7, b"{\"schema\":1}");
+        assert_eq!(decode_record(&record), Some((7, &b"{\"schema\":1}"[..])));
+        // Stale bytes of a longer previous record are ignored
+        let mut padded = record.clone();
+        padded.extend_from_slice(b"stale");
+        assert_eq!(decode_record(&padded).unwrap().0, 7);
+
+        let mut torn = record.clone();
+        torn[RECORD_HEADER_SIZE + 2] ^= 1;
+        assert_eq!(decode_record(&torn), None);
+        assert_eq!(decode_record(&record[..record.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_state_alternates_between_slots() {
+        let mut files = MemoryFiles::default();
+        let mut store = Store::open(&mut files).unwrap();
+        assert_eq!(store.state(), &StoreState::default());
+
+        store.state_mut().current = 1;
+        store.record(1, ORIGIN_APPLY, 100, "first");
+        store.save().unwrap();
+        store.state_mut().current = 2;
+        store.save().unwrap();
+        store.write_document(2, b"{}").unwrap();
+        drop(store);
+        assert!(files.files.contains_key("/etc/orion/config/state.0"));
+        assert!(files.files.contains_key("/etc/orion/config/state.1"));
+
+        // A torn write of the newest state falls back to the previous one
+        let mut store = Store::open(&mut files).unwrap();
+        assert_eq!(store.state().current, 2);
+        assert_eq!(store.state().history[0].comment, "first");
+        assert_eq!(store.document(2).unwrap(), b"{}");
+        assert_eq!(store.document(18), Err(STATUS_ENOENT));
+        drop(store);
+        files.files.get_mut("/etc/orion/config/state.0").unwrap()[RECORD_HEADER_SIZE] ^= 1;
+        assert_eq!(Store::open(&mut files).unwrap().state().current, 1);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut files = MemoryFiles::default();
+        let mut store = Store::open(&mut files).unwrap();
+        for version in 1..=MAX_HISTORY + 3 {
+            store.record(version, ORIGIN_APPLY, 0, &"é".repeat(200));
+        }
+        let history = &store.state().history;
+        assert_eq!(history.len(), MAX_HISTORY as usize);
+        assert_eq!(history[0].version, 4);
+        assert_eq!(history[0].comment.len(), MAX_COMMENT);
+    }
+}
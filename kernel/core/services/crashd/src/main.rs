@@ -0,0 +1,250 @@
+/*
+ * Orion Operating System - Crash Dump Server
+ *
+ * Keeps core dumps for postmortem analysis. Dumps reach the server two
+ * ways: the kernel spools the capture of a panic or of a process it
+ * killed on a fault (see spool.rs), and any process holding CAP_WRITE,
+ * a debugger or a supervisor, streams a dump it took itself (see
+ * protocol.rs). Either way the dump goes through the store, which
+ * compresses and deduplicates it onto the reserved crash volume and
+ * keeps it within the retention limits (see store.rs).
+ *
+ * Dumps are indexed by component and time; orion-storagectl and the
+ * management API list, fetch and delete them. Every stored and deleted
+ * dump is audited.
+ *
+ * Until the crash volume shows up the server retries opening it and
+ * answers ENODEV; the spool keeps the last kernel capture meanwhile.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+use orion_sys::{audit_emit, clock_get};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the dump index and dumps being compressed
+const HEAP_SIZE: usize = 8 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod protocol;
+mod spool;
+mod store;
+mod volume;
+
+use protocol::*;
+use spool::{Capture, SpoolFile};
+use store::DumpStore;
+use volume::{LvmVolume, CRASH_VOLUME};
+
+const CLOCK_ID_REALTIME: u32 = 1;
+
+const POLL_INTERVAL_NS: u64 = 100 * 1_000_000;
+
+/// Polls between two looks at the spool and the volume (one second)
+const SPOOL_POLLS: u64 = 10;
+
+/// Polls between two retention passes (one minute)
+const RETENTION_POLLS: u64 = 600;
+
+/// Owner of the uploads of spooled captures, never a process id
+const SPOOL_OWNER: u64 = u64::MAX;
+
+/// Audit event types emitted by the crash dump server (user range, see capabilities.c)
+const AUDIT_CRASH_STORED: u32 = 0x1701;
+const AUDIT_CRASH_DELETED: u32 = 0x1702;
+
+fn realtime_ns() -> u64 {
+    clock_get(CLOCK_ID_REALTIME).unwrap_or(0)
+}
+
+struct CrashServer {
+    /// None until the crash volume could be opened
+    store: Option<DumpStore<LvmVolume>>,
+    spool: SpoolFile,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+    polls: u64,
+}
+
+impl CrashServer {
+    fn new() -> Self {
+        Self {
+            store: None,
+            spool: SpoolFile::connect(),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+            polls: 0,
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+
+            if self.polls % SPOOL_POLLS == 0 {
+                self.open_store();
+                self.collect_spool();
+            }
+            if self.polls % RETENTION_POLLS == 0 {
+                if let Some(store) = self.store.as_mut() {
+                    let _ = store.expire(realtime_ns());
+                }
+            }
+            self.polls += 1;
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn open_store(&mut self) {
+        if self.store.is_some() {
+            return;
+        }
+        if let Ok(volume) = LvmVolume::open(IpcChannel::connect("lvm-advanced"), CRASH_VOLUME) {
+            self.store = DumpStore::open(volume).ok();
+        }
+    }
+
+    /// Move the capture waiting in the kernel spool into the store
+    fn collect_spool(&mut self) {
+        let Some(store) = self.store.as_mut() else {
+            return;
+        };
+        let Ok(Some(capture)) = self.spool.pending() else {
+            return;
+        };
+        let Capture { pid, time, component, reason, text, .. } = capture;
+        // A capture the store refuses stays spooled for the next look; the
+        // upload it left is dropped by the next begin
+        let stored = store.begin(SPOOL_OWNER, pid, time, &component, &reason).and_then(|upload| {
+            store.append(upload, SPOOL_OWNER, &text)?;
+            store.commit(upload, SPOOL_OWNER, realtime_ns())
+        });
+        if let Ok(id) = stored {
+            let _ = self.spool.clear();
+            let record =
+                format!("crash-stored id={} component={} pid={} size={} source=kernel", id, component, pid, text.len());
+            let _ = audit_emit(AUDIT_CRASH_STORED, record.as_bytes());
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let Some(request) = CrashRequest::decode(&message.data) else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+            return;
+        };
+
+        let rights = match request {
+            CrashRequest::Status | CrashRequest::List { .. } | CrashRequest::Read { .. } => CAP_READ,
+            CrashRequest::Delete { .. } | CrashRequest::SetRetention(_) => CAP_ADMIN,
+            _ => CAP_WRITE,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        self.open_store();
+        let Some(store) = self.store.as_mut() else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_ENODEV, &[]));
+            return;
+        };
+
+        let mut payload = Vec::new();
+        let result = match request {
+            CrashRequest::Status => {
+                encode_status(&store.stats(), &store.retention(), &mut payload);
+                Ok(())
+            }
+            CrashRequest::List { since, component } => {
+                let component = Some(component.as_str()).filter(|name| !name.is_empty());
+                for dump in store.list(component, since) {
+                    encode_dump(dump, &mut payload);
+                }
+                Ok(())
+            }
+            CrashRequest::Read { id, offset, max } => store.read(id, offset, max as usize).map(|data| payload = data),
+            CrashRequest::Delete { id } => {
+                let component = store.dump(id).map(|dump| dump.component.clone());
+                let result = store.remove(id);
+                if let Some(component) = component {
+                    let record = format!(
+                        "crash-deleted id={} component={} status={} sender={}",
+                        id,
+                        component,
+                        result.err().unwrap_or(STATUS_OK),
+                        message.sender
+                    );
+                    let _ = audit_emit(AUDIT_CRASH_DELETED, record.as_bytes());
+                }
+                result
+            }
+            CrashRequest::SetRetention(retention) => store.set_retention(retention, realtime_ns()),
+            CrashRequest::Begin { pid, time, component, reason } => {
+                let time = if time == 0 { realtime_ns() } else { time };
+                store
+                    .begin(message.sender, pid, time, &component, &reason)
+                    .map(|upload| payload.extend_from_slice(&upload.to_le_bytes()))
+            }
+            CrashRequest::Append { upload, data } => store.append(upload, message.sender, &data),
+            CrashRequest::Commit { upload } => match store.commit(upload, message.sender, realtime_ns()) {
+                Ok(id) => {
+                    payload.extend_from_slice(&id.to_le_bytes());
+                    if let Some(dump) = store.dump(id) {
+                        let record = format!(
+                            "crash-stored id={} component={} pid={} size={} sender={}",
+                            id, dump.component, dump.pid, dump.size, message.sender
+                        );
+                        let _ = audit_emit(AUDIT_CRASH_STORED, record.as_bytes());
+                    }
+                    Ok(())
+                }
+                Err(status) => Err(status),
+            },
+            CrashRequest::Abort { upload } => store.abort(upload, message.sender),
+        };
+
+        let status = result.err().unwrap_or(STATUS_OK);
+        if status != STATUS_OK {
+            payload.clear();
+        }
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = CrashServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
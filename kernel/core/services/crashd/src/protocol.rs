@@ -0,0 +1,213 @@
+/*
+ * Orion Operating System - Crash Dump Server Protocol
+ *
+ * IPC requests of the crash dump server. All fields are little-endian;
+ * every message starts with a 32-bit opcode and every reply starts with
+ * a 32-bit signed status (0 or a negative errno).
+ *
+ *   STATUS         (none)                    -> dumps:u32 chunks:u32
+ *                                               raw_bytes:u64 stored_bytes:u64
+ *                                               used_bytes:u64 capacity:u64
+ *                                               dedup_hits:u64 evicted:u64
+ *                                               retention
+ *   LIST           since:u64 component       -> { id:u32 time:u64 pid:u64
+ *                                                 size:u64 component reason }*
+ *   READ           id:u32 offset:u64 max:u32 -> data
+ *   DELETE         id:u32                    -> (empty)
+ *   SET_RETENTION  retention                 -> (empty)
+ *   BEGIN          pid:u64 time:u64
+ *                  component reason          -> upload:u32
+ *   APPEND         upload:u32 data...        -> (empty)
+ *   COMMIT         upload:u32                -> id:u32
+ *   ABORT          upload:u32                -> (empty)
+ *
+ * A retention is max_dumps:u32 max_per_component:u32 max_bytes:u64
+ * max_age_ns:u64, with 0 disabling the byte or age limit. Times are the
+ * realtime clock in nanoseconds. LIST returns the dumps taken from
+ * `since` on, oldest first, of one component or of all of them when
+ * `component` is empty. READ answers short at the end of a chunk and
+ * empty at the end of the dump.
+ *
+ * A dump is sent with BEGIN, any number of APPENDs and COMMIT; `time` 0
+ * stands for the time BEGIN arrives. An upload belongs to the process
+ * that began it, and a new BEGIN of that process drops the one it left.
+ *
+ * STATUS, LIST and READ need CAP_READ, uploads CAP_WRITE, DELETE and
+ * SET_RETENTION CAP_ADMIN. Every request answers ENODEV while the crash
+ * volume is missing.
+ *
+ * Strings are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::store::{Dump, Retention, StoreStats, MAX_COMPONENT, MAX_REASON};
+
+// Opcodes
+pub const OP_STATUS: u32 = 1;
+pub const OP_LIST: u32 = 2;
+pub const OP_READ: u32 = 3;
+pub const OP_DELETE: u32 = 4;
+pub const OP_SET_RETENTION: u32 = 5;
+pub const OP_BEGIN: u32 = 6;
+pub const OP_APPEND: u32 = 7;
+pub const OP_COMMIT: u32 = 8;
+pub const OP_ABORT: u32 = 9;
+
+/// Largest READ, bounded by what one IPC reply carries
+pub const MAX_READ: u32 = 60 * 1024;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_ENODEV: i32 = -19;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CrashRequest {
+    Status,
+    List { since: u64, component: String },
+    Read { id: u32, offset: u64, max: u32 },
+    Delete { id: u32 },
+    SetRetention(Retention),
+    Begin { pid: u64, time: u64, component: String, reason: String },
+    Append { upload: u32, data: Vec<u8> },
+    Commit { upload: u32 },
+    Abort { upload: u32 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, utf-8 bytes` field, returning it with the offset after it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + len))
+}
+
+fn write_string(text: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+impl CrashRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_STATUS => Some(CrashRequest::Status),
+            OP_LIST => {
+                let (component, _) = read_string(data, 12)?;
+                (component.len() <= MAX_COMPONENT)
+                    .then_some(CrashRequest::List { since: read_u64(data, 4)?, component })
+            }
+            OP_READ => Some(CrashRequest::Read {
+                id: read_u32(data, 4)?,
+                offset: read_u64(data, 8)?,
+                max: read_u32(data, 16)?.min(MAX_READ),
+            }),
+            OP_DELETE => Some(CrashRequest::Delete { id: read_u32(data, 4)? }),
+            OP_SET_RETENTION => Some(CrashRequest::SetRetention(Retention {
+                max_dumps: read_u32(data, 4)?,
+                max_per_component: read_u32(data, 8)?,
+                max_bytes: read_u64(data, 12)?,
+                max_age_ns: read_u64(data, 20)?,
+            })),
+            OP_BEGIN => {
+                let (component, next) = read_string(data, 20)?;
+                let (reason, _) = read_string(data, next)?;
+                if component.is_empty() || component.len() > MAX_COMPONENT || reason.len() > MAX_REASON {
+                    return None;
+                }
+                Some(CrashRequest::Begin { pid: read_u64(data, 4)?, time: read_u64(data, 12)?, component, reason })
+            }
+            OP_APPEND => Some(CrashRequest::Append { upload: read_u32(data, 4)?, data: data.get(8..)?.to_vec() }),
+            OP_COMMIT => Some(CrashRequest::Commit { upload: read_u32(data, 4)? }),
+            OP_ABORT => Some(CrashRequest::Abort { upload: read_u32(data, 4)? }),
+            _ => None,
+        }
+    }
+}
+
+/// Append a STATUS reply
+pub fn encode_status(stats: &StoreStats, retention: &Retention, out: &mut Vec<u8>) {
+    out.extend_from_slice(&stats.dumps.to_le_bytes());
+    out.extend_from_slice(&stats.chunks.to_le_bytes());
+    for value in
+        [stats.raw_bytes, stats.stored_bytes, stats.used_bytes, stats.capacity, stats.dedup_hits, stats.evicted]
+    {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&retention.max_dumps.to_le_bytes());
+    out.extend_from_slice(&retention.max_per_component.to_le_bytes());
+    out.extend_from_slice(&retention.max_bytes.to_le_bytes());
+    out.extend_from_slice(&retention.max_age_ns.to_le_bytes());
+}
+
+/// Append a LIST record
+pub fn encode_dump(dump: &Dump, out: &mut Vec<u8>) {
+    out.extend_from_slice(&dump.id.to_le_bytes());
+    out.extend_from_slice(&dump.time.to_le_bytes());
+    out.extend_from_slice(&dump.pid.to_le_bytes());
+    out.extend_from_slice(&dump.size.to_le_bytes());
+    write_string(&dump.component, out);
+    write_string(&dump.reason, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut begin = OP_BEGIN.to_le_bytes().to_vec();
+        begin.extend_from_slice(&42u64.to_le_bytes());
+        begin.extend_from_slice(&0u64.to_le_bytes());
+        write_string("netd", &mut begin);
+        write_string("Guard page hit", &mut begin);
+        let expected = CrashRequest::Begin {
+            pid: 42,
+            time: 0,
+            component: String::from("netd"),
+            reason: String::from("Guard page hit"),
+        };
+        assert_eq!(CrashRequest::decode(&begin), Some(expected));
+        assert_eq!(CrashRequest::decode(&begin[..begin.len() - 1]), None);
+
+        let mut nameless = OP_BEGIN.to_le_bytes().to_vec();
+        nameless.extend_from_slice(&[0; 16]);
+        write_string("", &mut nameless);
+        write_string("", &mut nameless);
+        assert_eq!(CrashRequest::decode(&nameless), None);
+
+        let mut list = OP_LIST.to_le_bytes().to_vec();
+        list.extend_from_slice(&5u64.to_le_bytes());
+        write_string("", &mut list);
+        assert_eq!(CrashRequest::decode(&list), Some(CrashRequest::List { since: 5, component: String::new() }));
+
+        let mut read = OP_READ.to_le_bytes().to_vec();
+        read.extend_from_slice(&3u32.to_le_bytes());
+        read.extend_from_slice(&4096u64.to_le_bytes());
+        read.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(CrashRequest::decode(&read), Some(CrashRequest::Read { id: 3, offset: 4096, max: MAX_READ }));
+    }
+}
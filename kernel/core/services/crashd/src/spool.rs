@@ -0,0 +1,193 @@
+/*
+ * Orion Operating System - Kernel Crash Spool
+ *
+ * The kernel writes each core dump it takes, for a panic or for a process
+ * it kills on a fault, to one spool file (CRASH_SPOOL_PATH of
+ * utilities/panic.h): a 64-byte header followed by the text of the dump.
+ *
+ *   magic "OKCR"  length:u32  sequence:u64  realtime_ns:u64  pid:u64
+ *   component[32] (NUL padded)
+ *
+ * A nonzero sequence marks a capture not stored yet; the server clears it
+ * once the dump is in the store. A panic capture survives the reboot in
+ * the file and is picked up when the server starts; a later capture
+ * replaces one the server did not poll in time.
+ *
+ * The file is reached through the direct file requests of the file
+ * system server (services/fs/src/files.rs).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+use crate::protocol::{STATUS_EIO, STATUS_ENOENT, STATUS_OK};
+
+pub const SPOOL_PATH: &str = "/var/crash/spool";
+
+const SPOOL_MAGIC: &[u8; 4] = b"OKCR";
+const SPOOL_HEADER_SIZE: usize = 64;
+const SEQUENCE_OFFSET: u64 = 8;
+const COMPONENT_SIZE: usize = 32;
+
+/// Largest capture the kernel writes
+const MAX_SPOOL_SIZE: u32 = 4096;
+
+// File system direct file requests
+const FS_OP_OPEN: u32 = 0x41;
+const FS_OP_READ_AT: u32 = 0x42;
+const FS_OP_WRITE_AT: u32 = 0x43;
+const FS_OP_SYNC: u32 = 0x44;
+const FS_OP_CLOSE: u32 = 0x45;
+const FS_OPEN_READ: u32 = 0o1;
+const FS_OPEN_WRITE: u32 = 0o2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub sequence: u64,
+    pub time: u64,
+    /// Faulting process, 0 for a kernel panic
+    pub pid: u64,
+    pub component: String,
+    /// "Reason:" line of the dump
+    pub reason: String,
+    pub text: Vec<u8>,
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// Capture waiting in the spool file contents `data`, None when it holds
+/// none
+pub fn decode_capture(data: &[u8]) -> Option<Capture> {
+    if data.get(..4)? != SPOOL_MAGIC {
+        return None;
+    }
+    let length = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+    let sequence = read_u64(data, 8)?;
+    if sequence == 0 {
+        return None;
+    }
+    let component = data.get(32..32 + COMPONENT_SIZE)?;
+    let component = &component[..component.iter().position(|byte| *byte == 0).unwrap_or(COMPONENT_SIZE)];
+    let component = match core::str::from_utf8(component) {
+        Ok(name) if !name.is_empty() => String::from(name),
+        _ => String::from("kernel"),
+    };
+    let text = data.get(SPOOL_HEADER_SIZE..SPOOL_HEADER_SIZE + length)?.to_vec();
+    let reason = core::str::from_utf8(&text)
+        .ok()
+        .and_then(|text| text.lines().find_map(|line| line.strip_prefix("Reason: ")))
+        .map(String::from)
+        .unwrap_or_default();
+    Some(Capture { sequence, time: read_u64(data, 16)?, pid: read_u64(data, 24)?, component, reason, text })
+}
+
+pub struct SpoolFile {
+    channel: IpcChannel,
+}
+
+impl SpoolFile {
+    pub fn connect() -> Self {
+        Self { channel: IpcChannel::connect("fs") }
+    }
+
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.channel.call(request).map_err(|_| STATUS_EIO)?;
+        if response.len() < 4 {
+            return Err(STATUS_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn open(&mut self, flags: u32) -> Result<u32, i32> {
+        let mut request = FS_OP_OPEN.to_le_bytes().to_vec();
+        request.extend_from_slice(&flags.to_le_bytes());
+        request.extend_from_slice(&(SPOOL_PATH.len() as u32).to_le_bytes());
+        request.extend_from_slice(SPOOL_PATH.as_bytes());
+        let payload = self.call(&request)?;
+        Ok(u32::from_le_bytes(payload.get(..4).ok_or(STATUS_EIO)?.try_into().unwrap()))
+    }
+
+    fn close(&mut self, handle: u32) {
+        let mut request = FS_OP_CLOSE.to_le_bytes().to_vec();
+        request.extend_from_slice(&handle.to_le_bytes());
+        let _ = self.call(&request);
+    }
+
+    /// Capture waiting in the spool, if any
+    pub fn pending(&mut self) -> Result<Option<Capture>, i32> {
+        let handle = match self.open(FS_OPEN_READ) {
+            Ok(handle) => handle,
+            Err(STATUS_ENOENT) => return Ok(None),
+            Err(status) => return Err(status),
+        };
+        let mut request = FS_OP_READ_AT.to_le_bytes().to_vec();
+        request.extend_from_slice(&handle.to_le_bytes());
+        request.extend_from_slice(&0u64.to_le_bytes());
+        request.extend_from_slice(&MAX_SPOOL_SIZE.to_le_bytes());
+        let data = self.call(&request);
+        self.close(handle);
+        Ok(decode_capture(&data?))
+    }
+
+    /// Mark the capture as stored
+    pub fn clear(&mut self) -> Result<(), i32> {
+        let handle = self.open(FS_OPEN_READ | FS_OPEN_WRITE)?;
+        let mut request = FS_OP_WRITE_AT.to_le_bytes().to_vec();
+        request.extend_from_slice(&handle.to_le_bytes());
+        request.extend_from_slice(&SEQUENCE_OFFSET.to_le_bytes());
+        request.extend_from_slice(&0u64.to_le_bytes());
+        let mut result = self.call(&request).map(|_| ());
+        if result.is_ok() {
+            let mut sync = FS_OP_SYNC.to_le_bytes().to_vec();
+            sync.extend_from_slice(&handle.to_le_bytes());
+            result = self.call(&sync).map(|_| ());
+        }
+        self.close(handle);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_captures() {
+        let text = b"=== ORION OS CORE DUMP ===\nReason: Guard page hit\nActive processes: 12\n";
+        let mut data = Vec::from(&SPOOL_MAGIC[..]);
+        data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&1_700_000_000_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&42u64.to_le_bytes());
+        let mut component = [0u8; COMPONENT_SIZE];
+        component[..4].copy_from_slice(b"netd");
+        data.extend_from_slice(&component);
+        data.extend_from_slice(text);
+        // Left over from a longer capture
+        data.extend_from_slice(b"stale");
+
+        let capture = decode_capture(&data).unwrap();
+        assert_eq!(capture.sequence, 3);
+        assert_eq!(capture.pid, 42);
+        assert_eq!(capture.component, "netd");
+        assert_eq!(capture.reason, "Guard page hit");
+        assert_eq!(capture.text, text);
+
+        assert!(decode_capture(&data[..data.len() - 10]).is_none());
+        data[8..16].fill(0);
+        assert!(decode_capture(&data).is_none());
+    }
+}
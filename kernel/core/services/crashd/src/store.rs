@@ -0,0 +1,973 @@
+/*
+ * Orion Operating System - Crash Dump Store
+ *
+ * Dumps kept on the reserved crash volume. A dump is streamed in and cut
+ * into CHUNK_SIZE chunks; each chunk is named by its SHA-256 digest and
+ * stored once, LZ4 compressed unless that does not make it smaller, so a
+ * component crashing the same way again costs little more than its index
+ * entry. Chunks are counted references of the dumps naming them and
+ * their space is reused once no dump does.
+ *
+ * Volume layout, in UNIT_SIZE units:
+ *
+ *   0 .. INDEX_UNITS                 index slot 0
+ *   INDEX_UNITS .. 2 * INDEX_UNITS   index slot 1
+ *   2 * INDEX_UNITS ..               chunks, each starting on a unit
+ *
+ * The index lists the retention limits, every chunk and every dump with
+ * the chunks it is made of. Each save goes to the slot not holding the
+ * latest index, as a record with a sequence number and an FNV-1a checksum
+ * of its body, and the intact record with the highest sequence wins when
+ * the store is opened. Chunks are flushed before the index naming them
+ * is written, and the space of dropped chunks is only reused after an
+ * index no longer naming them was saved, so a crash of the server leaves
+ * the previous index valid. Reads check every chunk against its digest.
+ *
+ * Retention drops the oldest dumps beyond the total count, the count per
+ * component, the stored bytes or the age limits. When a new chunk does
+ * not fit, the oldest dumps make way for it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_crypto::sha256::{Sha256, SHA256_DIGEST_SIZE};
+
+use crate::protocol::{STATUS_EBUSY, STATUS_EINVAL, STATUS_EIO, STATUS_ENOENT, STATUS_ENOSPC, STATUS_EPERM};
+
+/// Allocation unit of the volume
+pub const UNIT_SIZE: u64 = 4096;
+
+/// Dumps are cut into chunks of this many bytes, the last one shorter
+pub const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Size of one index slot: 2 MiB
+const INDEX_UNITS: u64 = 512;
+const INDEX_BYTES: usize = (INDEX_UNITS * UNIT_SIZE) as usize;
+const DATA_START: u64 = 2 * INDEX_UNITS;
+
+/// Smallest volume worth using: the index slots and 1 MiB of chunks
+pub const MIN_VOLUME_SIZE: u64 = (DATA_START + 256) * UNIT_SIZE;
+
+const INDEX_MAGIC: &[u8; 4] = b"OCRD";
+const INDEX_HEADER_SIZE: usize = 20;
+
+pub const MAX_COMPONENT: usize = 64;
+pub const MAX_REASON: usize = 256;
+
+/// Dumps being received at once
+pub const MAX_UPLOADS: usize = 4;
+
+/// Highest total count a retention may allow
+pub const MAX_DUMPS: u32 = 1024;
+
+const CHUNK_COMPRESSED: u32 = 1 << 0;
+
+/// Limits dumps are kept within; 0 disables a byte or age limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub max_dumps: u32,
+    pub max_per_component: u32,
+    /// Space the chunks may take, below the size of the volume
+    pub max_bytes: u64,
+    /// Dumps older than this are dropped
+    pub max_age_ns: u64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self { max_dumps: 64, max_per_component: 8, max_bytes: 0, max_age_ns: 30 * 24 * 3600 * 1_000_000_000 }
+    }
+}
+
+impl Retention {
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_DUMPS).contains(&self.max_dumps) && (1..=self.max_dumps).contains(&self.max_per_component)
+    }
+}
+
+/// Storage behind the store. Offsets and lengths are multiples of UNIT_SIZE
+pub trait Volume {
+    fn size(&self) -> u64;
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32>;
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32>;
+    fn flush(&mut self) -> Result<(), i32>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dump {
+    pub id: u32,
+    /// Realtime clock of the crash, in nanoseconds
+    pub time: u64,
+    /// Process that crashed, 0 for the kernel
+    pub pid: u64,
+    pub component: String,
+    pub reason: String,
+    /// Bytes of the dump as it was received
+    pub size: u64,
+    chunks: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    pub dumps: u32,
+    pub chunks: u32,
+    /// Bytes of every dump as received
+    pub raw_bytes: u64,
+    /// Bytes of the chunks once compressed, each counted once
+    pub stored_bytes: u64,
+    /// Space the chunks take, in whole units
+    pub used_bytes: u64,
+    /// Space for chunks, within the retention byte limit
+    pub capacity: u64,
+    /// Chunks found already stored
+    pub dedup_hits: u64,
+    /// Dumps dropped by the retention limits or to make room
+    pub evicted: u64,
+}
+
+struct Chunk {
+    digest: [u8; SHA256_DIGEST_SIZE],
+    /// First unit, counted from the start of the volume
+    unit: u64,
+    stored: u32,
+    raw: u32,
+    compressed: bool,
+    /// Dumps and uploads naming the chunk
+    refs: u32,
+}
+
+fn units(bytes: u64) -> u64 {
+    bytes.div_ceil(UNIT_SIZE)
+}
+
+/// Dump being received
+struct Upload {
+    owner: u64,
+    pid: u64,
+    time: u64,
+    component: String,
+    reason: String,
+    /// Bytes of the chunk not complete yet
+    pending: Vec<u8>,
+    chunks: Vec<u32>,
+    size: u64,
+}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+/// Little-endian reader over an index body
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(self.bytes(8)?);
+        Some(u64::from_le_bytes(raw))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        Some(String::from(core::str::from_utf8(self.bytes(len)?).ok()?))
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn encode_record(sequence: u64, body: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(INDEX_HEADER_SIZE + body.len());
+    record.extend_from_slice(INDEX_MAGIC);
+    record.extend_from_slice(&sequence.to_le_bytes());
+    record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    record.extend_from_slice(&fnv1a(body).to_le_bytes());
+    record.extend_from_slice(body);
+    record
+}
+
+/// Sequence and body of an intact record
+fn decode_record(data: &[u8]) -> Option<(u64, &[u8])> {
+    if data.get(..4)? != INDEX_MAGIC {
+        return None;
+    }
+    let mut reader = Reader { data, offset: 4 };
+    let sequence = reader.u64()?;
+    let length = reader.u32()? as usize;
+    let checksum = reader.u32()?;
+    let body = reader.bytes(length)?;
+    (fnv1a(body) == checksum).then_some((sequence, body))
+}
+
+/// Contents of an index
+struct Index {
+    retention: Retention,
+    next_dump: u32,
+    chunks: BTreeMap<u32, Chunk>,
+    dumps: Vec<Dump>,
+}
+
+impl Index {
+    fn decode(body: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data: body, offset: 0 };
+        let retention = Retention {
+            max_dumps: reader.u32()?,
+            max_per_component: reader.u32()?,
+            max_bytes: reader.u64()?,
+            max_age_ns: reader.u64()?,
+        };
+        let next_dump = reader.u32()?;
+        let mut chunks = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let id = reader.u32()?;
+            let chunk = Chunk {
+                digest: reader.bytes(SHA256_DIGEST_SIZE)?.try_into().ok()?,
+                unit: reader.u64()?,
+                stored: reader.u32()?,
+                raw: reader.u32()?,
+                compressed: reader.u32()? & CHUNK_COMPRESSED != 0,
+                refs: 0,
+            };
+            chunks.insert(id, chunk);
+        }
+        let mut dumps = Vec::new();
+        for _ in 0..reader.u32()? {
+            let mut dump = Dump {
+                id: reader.u32()?,
+                time: reader.u64()?,
+                pid: reader.u64()?,
+                size: reader.u64()?,
+                component: reader.string()?,
+                reason: reader.string()?,
+                chunks: Vec::new(),
+            };
+            for _ in 0..reader.u32()? {
+                dump.chunks.push(reader.u32()?);
+            }
+            dumps.push(dump);
+        }
+        Some(Self { retention, next_dump, chunks, dumps })
+    }
+}
+
+pub struct DumpStore<V: Volume> {
+    volume: V,
+    retention: Retention,
+    dumps: BTreeMap<u32, Dump>,
+    /// (component, time, id) of every dump
+    by_component: BTreeSet<(String, u64, u32)>,
+    /// (time, id) of every dump, oldest first
+    by_time: BTreeSet<(u64, u32)>,
+    chunks: BTreeMap<u32, Chunk>,
+    by_digest: BTreeMap<[u8; SHA256_DIGEST_SIZE], u32>,
+    uploads: BTreeMap<u32, Upload>,
+    next_dump: u32,
+    next_chunk: u32,
+    next_upload: u32,
+    /// Allocated units of the chunk area
+    bitmap: Vec<u64>,
+    data_units: u64,
+    used_units: u64,
+    /// Extents of dropped chunks, free once the saved index no longer
+    /// names them
+    released: Vec<(u64, u64)>,
+    sequence: u64,
+    dedup_hits: u64,
+    evicted: u64,
+    /// Last chunk read back, for reads walking through a dump
+    cache: Option<(u32, Vec<u8>)>,
+}
+
+impl<V: Volume> DumpStore<V> {
+    /// Load the latest intact index, or start an empty store on a blank
+    /// volume
+    pub fn open(mut volume: V) -> Result<Self, i32> {
+        let size = volume.size();
+        if size < MIN_VOLUME_SIZE {
+            return Err(STATUS_EINVAL);
+        }
+        let mut latest: Option<(u64, Index)> = None;
+        let mut slot = vec![0u8; INDEX_BYTES];
+        for number in 0..2 {
+            volume.read(number * INDEX_UNITS * UNIT_SIZE, &mut slot)?;
+            let Some((sequence, body)) = decode_record(&slot) else {
+                continue;
+            };
+            if latest.as_ref().is_some_and(|(newest, _)| *newest >= sequence) {
+                continue;
+            }
+            if let Some(index) = Index::decode(body) {
+                latest = Some((sequence, index));
+            }
+        }
+
+        let data_units = size / UNIT_SIZE - DATA_START;
+        let mut store = Self {
+            volume,
+            retention: Retention::default(),
+            dumps: BTreeMap::new(),
+            by_component: BTreeSet::new(),
+            by_time: BTreeSet::new(),
+            chunks: BTreeMap::new(),
+            by_digest: BTreeMap::new(),
+            uploads: BTreeMap::new(),
+            next_dump: 1,
+            next_chunk: 1,
+            next_upload: 1,
+            bitmap: vec![0; data_units.div_ceil(64) as usize],
+            data_units,
+            used_units: 0,
+            released: Vec::new(),
+            sequence: 0,
+            dedup_hits: 0,
+            evicted: 0,
+            cache: None,
+        };
+        let Some((sequence, index)) = latest else {
+            store.save()?;
+            return Ok(store);
+        };
+
+        store.sequence = sequence;
+        store.retention = index.retention;
+        store.next_dump = index.next_dump;
+        store.chunks = index.chunks;
+        for dump in index.dumps {
+            if dump.chunks.iter().any(|id| !store.chunks.contains_key(id)) {
+                return Err(STATUS_EIO);
+            }
+            for id in dump.chunks.iter() {
+                store.chunks.get_mut(id).unwrap().refs += 1;
+            }
+            store.insert(dump);
+        }
+        // Chunks no dump names any more were freed after the index was saved
+        store.chunks.retain(|_, chunk| chunk.refs > 0);
+        let extents: Vec<(u64, u64)> =
+            store.chunks.values().map(|chunk| (chunk.unit, units(chunk.stored as u64))).collect();
+        for (unit, count) in extents {
+            if unit < DATA_START || unit + count > DATA_START + data_units {
+                return Err(STATUS_EIO);
+            }
+            store.mark(unit, count, true);
+            store.used_units += count;
+        }
+        for (id, chunk) in store.chunks.iter() {
+            store.by_digest.insert(chunk.digest, *id);
+        }
+        store.next_chunk = store.chunks.keys().next_back().map_or(1, |id| id + 1);
+        Ok(store)
+    }
+
+    fn insert(&mut self, dump: Dump) {
+        self.by_component.insert((dump.component.clone(), dump.time, dump.id));
+        self.by_time.insert((dump.time, dump.id));
+        self.dumps.insert(dump.id, dump);
+    }
+
+    pub fn retention(&self) -> Retention {
+        self.retention
+    }
+
+    pub fn stats(&self) -> StoreStats {
+        let capacity = match self.retention.max_bytes {
+            0 => self.data_units * UNIT_SIZE,
+            limit => limit.min(self.data_units * UNIT_SIZE),
+        };
+        StoreStats {
+            dumps: self.dumps.len() as u32,
+            chunks: self.chunks.len() as u32,
+            raw_bytes: self.dumps.values().map(|dump| dump.size).sum(),
+            stored_bytes: self.chunks.values().map(|chunk| chunk.stored as u64).sum(),
+            used_bytes: self.used_units * UNIT_SIZE,
+            capacity,
+            dedup_hits: self.dedup_hits,
+            evicted: self.evicted,
+        }
+    }
+
+    pub fn dump(&self, id: u32) -> Option<&Dump> {
+        self.dumps.get(&id)
+    }
+
+    /// Dumps of `component`, or of every component, from `since` on,
+    /// oldest first
+    pub fn list(&self, component: Option<&str>, since: u64) -> Vec<&Dump> {
+        match component {
+            Some(component) => self
+                .by_component
+                .range((String::from(component), since, 0)..)
+                .take_while(|(name, _, _)| name == component)
+                .map(|(_, _, id)| &self.dumps[id])
+                .collect(),
+            None => self.by_time.range((since, 0)..).map(|(_, id)| &self.dumps[id]).collect(),
+        }
+    }
+
+    // ========================================
+    // Space
+    // ========================================
+
+    fn mark(&mut self, unit: u64, count: u64, used: bool) {
+        for bit in unit - DATA_START..unit - DATA_START + count {
+            let word = &mut self.bitmap[(bit / 64) as usize];
+            if used {
+                *word |= 1 << (bit % 64);
+            } else {
+                *word &= !(1 << (bit % 64));
+            }
+        }
+    }
+
+    /// First free run of `count` units within the byte limit
+    fn allocate(&mut self, count: u64) -> Option<u64> {
+        if self.retention.max_bytes != 0 && (self.used_units + count) * UNIT_SIZE > self.retention.max_bytes {
+            return None;
+        }
+        let mut run = 0;
+        for bit in 0..self.data_units {
+            if self.bitmap[(bit / 64) as usize] & (1 << (bit % 64)) != 0 {
+                run = 0;
+                continue;
+            }
+            run += 1;
+            if run == count {
+                let unit = DATA_START + bit + 1 - count;
+                self.mark(unit, count, true);
+                self.used_units += count;
+                return Some(unit);
+            }
+        }
+        None
+    }
+
+    fn release_chunk(&mut self, id: u32) {
+        let Some(chunk) = self.chunks.get_mut(&id) else {
+            return;
+        };
+        chunk.refs -= 1;
+        if chunk.refs > 0 {
+            return;
+        }
+        let chunk = self.chunks.remove(&id).unwrap();
+        let count = units(chunk.stored as u64);
+        self.by_digest.remove(&chunk.digest);
+        self.released.push((chunk.unit, count));
+        self.used_units -= count;
+        if self.cache.as_ref().is_some_and(|(cached, _)| *cached == id) {
+            self.cache = None;
+        }
+    }
+
+    fn drop_dump(&mut self, id: u32) -> bool {
+        let Some(dump) = self.dumps.remove(&id) else {
+            return false;
+        };
+        self.by_component.remove(&(dump.component, dump.time, id));
+        self.by_time.remove(&(dump.time, id));
+        for chunk in dump.chunks {
+            self.release_chunk(chunk);
+        }
+        true
+    }
+
+    fn evict_oldest(&mut self) -> bool {
+        let Some(&(_, id)) = self.by_time.iter().next() else {
+            return false;
+        };
+        self.evicted += 1;
+        self.drop_dump(id)
+    }
+
+    /// Persist the index into the slot the previous save did not use,
+    /// dropping the oldest dumps while it does not fit
+    fn save(&mut self) -> Result<(), i32> {
+        let body = loop {
+            let body = self.encode();
+            if INDEX_HEADER_SIZE + body.len() <= INDEX_BYTES {
+                break body;
+            }
+            if !self.evict_oldest() {
+                return Err(STATUS_ENOSPC);
+            }
+        };
+        // Chunks reach the volume before the index naming them
+        self.volume.flush()?;
+        let sequence = self.sequence + 1;
+        let mut record = encode_record(sequence, &body);
+        record.resize(units(record.len() as u64) as usize * UNIT_SIZE as usize, 0);
+        self.volume.write((sequence % 2) * INDEX_UNITS * UNIT_SIZE, &record)?;
+        self.volume.flush()?;
+        self.sequence = sequence;
+        for (unit, count) in core::mem::take(&mut self.released) {
+            self.mark(unit, count, false);
+        }
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let retention = &self.retention;
+        out.extend_from_slice(&retention.max_dumps.to_le_bytes());
+        out.extend_from_slice(&retention.max_per_component.to_le_bytes());
+        out.extend_from_slice(&retention.max_bytes.to_le_bytes());
+        out.extend_from_slice(&retention.max_age_ns.to_le_bytes());
+        out.extend_from_slice(&self.next_dump.to_le_bytes());
+
+        // Chunks only named by uploads are left out: they are free again
+        // if the server restarts before the upload is committed
+        let named: BTreeSet<u32> = self.dumps.values().flat_map(|dump| dump.chunks.iter().copied()).collect();
+        out.extend_from_slice(&(named.len() as u32).to_le_bytes());
+        for id in named {
+            let chunk = &self.chunks[&id];
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&chunk.digest);
+            out.extend_from_slice(&chunk.unit.to_le_bytes());
+            out.extend_from_slice(&chunk.stored.to_le_bytes());
+            out.extend_from_slice(&chunk.raw.to_le_bytes());
+            let flags = if chunk.compressed { CHUNK_COMPRESSED } else { 0 };
+            out.extend_from_slice(&flags.to_le_bytes());
+        }
+
+        out.extend_from_slice(&(self.dumps.len() as u32).to_le_bytes());
+        for dump in self.dumps.values() {
+            out.extend_from_slice(&dump.id.to_le_bytes());
+            out.extend_from_slice(&dump.time.to_le_bytes());
+            out.extend_from_slice(&dump.pid.to_le_bytes());
+            out.extend_from_slice(&dump.size.to_le_bytes());
+            put_string(&mut out, &dump.component);
+            put_string(&mut out, &dump.reason);
+            out.extend_from_slice(&(dump.chunks.len() as u32).to_le_bytes());
+            for id in dump.chunks.iter() {
+                out.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    // ========================================
+    // Retention
+    // ========================================
+
+    /// Dumps the limits no longer allow at `now`
+    fn victims(&self, now: u64) -> BTreeSet<u32> {
+        let retention = &self.retention;
+        let mut victims = BTreeSet::new();
+        if retention.max_age_ns != 0 {
+            for (time, id) in self.by_time.iter() {
+                if time.saturating_add(retention.max_age_ns) < now {
+                    victims.insert(*id);
+                }
+            }
+        }
+
+        // Newest first within each component
+        let mut current: Option<&str> = None;
+        let mut kept = 0;
+        for (component, _, id) in self.by_component.iter().rev() {
+            if current != Some(component.as_str()) {
+                current = Some(component.as_str());
+                kept = 0;
+            }
+            if victims.contains(id) {
+                continue;
+            }
+            kept += 1;
+            if kept > retention.max_per_component {
+                victims.insert(*id);
+            }
+        }
+
+        let survivors: Vec<u32> = self.by_time.iter().map(|(_, id)| *id).filter(|id| !victims.contains(id)).collect();
+        let excess = survivors.len().saturating_sub(retention.max_dumps as usize);
+        victims.extend(survivors[..excess].iter().copied());
+        victims
+    }
+
+    /// Apply the limits at `now`, saving the index when dumps were dropped;
+    /// returns how many were
+    pub fn expire(&mut self, now: u64) -> Result<u32, i32> {
+        let victims = self.victims(now);
+        if victims.is_empty() {
+            return Ok(0);
+        }
+        for id in victims.iter() {
+            self.drop_dump(*id);
+        }
+        self.evicted += victims.len() as u64;
+        self.save()?;
+        Ok(victims.len() as u32)
+    }
+
+    pub fn set_retention(&mut self, retention: Retention, now: u64) -> Result<(), i32> {
+        if !retention.is_valid() {
+            return Err(STATUS_EINVAL);
+        }
+        self.retention = retention;
+        // Space beyond a lowered byte limit is given back oldest first
+        while retention.max_bytes != 0 && self.used_units * UNIT_SIZE > retention.max_bytes && self.evict_oldest() {}
+        self.expire(now)?;
+        self.save()
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<(), i32> {
+        if !self.drop_dump(id) {
+            return Err(STATUS_ENOENT);
+        }
+        self.save()
+    }
+
+    // ========================================
+    // Uploads
+    // ========================================
+
+    /// Start receiving a dump for `owner`; a previous upload of the same
+    /// owner is dropped
+    pub fn begin(&mut self, owner: u64, pid: u64, time: u64, component: &str, reason: &str) -> Result<u32, i32> {
+        if component.is_empty() || component.len() > MAX_COMPONENT || reason.len() > MAX_REASON {
+            return Err(STATUS_EINVAL);
+        }
+        let previous: Vec<u32> =
+            self.uploads.iter().filter(|(_, upload)| upload.owner == owner).map(|(id, _)| *id).collect();
+        for id in previous {
+            self.abort(id, owner)?;
+        }
+        if self.uploads.len() >= MAX_UPLOADS {
+            return Err(STATUS_EBUSY);
+        }
+
+        let id = self.next_upload;
+        self.next_upload = self.next_upload.wrapping_add(1).max(1);
+        let upload = Upload {
+            owner,
+            pid,
+            time,
+            component: String::from(component),
+            reason: String::from(reason),
+            pending: Vec::new(),
+            chunks: Vec::new(),
+            size: 0,
+        };
+        self.uploads.insert(id, upload);
+        Ok(id)
+    }
+
+    fn upload_of(&mut self, id: u32, owner: u64) -> Result<&mut Upload, i32> {
+        match self.uploads.get_mut(&id) {
+            None => Err(STATUS_ENOENT),
+            Some(upload) if upload.owner != owner => Err(STATUS_EPERM),
+            Some(upload) => Ok(upload),
+        }
+    }
+
+    pub fn append(&mut self, id: u32, owner: u64, mut data: &[u8]) -> Result<(), i32> {
+        self.upload_of(id, owner)?;
+        while !data.is_empty() {
+            let upload = self.uploads.get_mut(&id).unwrap();
+            let take = (CHUNK_SIZE - upload.pending.len()).min(data.len());
+            upload.pending.extend_from_slice(&data[..take]);
+            upload.size += take as u64;
+            data = &data[take..];
+            if upload.pending.len() == CHUNK_SIZE {
+                let raw = core::mem::take(&mut upload.pending);
+                let chunk = self.put_chunk(&raw)?;
+                self.uploads.get_mut(&id).unwrap().chunks.push(chunk);
+            }
+        }
+        Ok(())
+    }
+
+    /// Store the rest of the upload as a dump and apply the limits;
+    /// returns the dump id
+    pub fn commit(&mut self, id: u32, owner: u64, now: u64) -> Result<u32, i32> {
+        let upload = self.upload_of(id, owner)?;
+        if upload.size == 0 {
+            return Err(STATUS_EINVAL);
+        }
+        if !upload.pending.is_empty() {
+            let raw = core::mem::take(&mut upload.pending);
+            match self.put_chunk(&raw) {
+                Ok(chunk) => self.uploads.get_mut(&id).unwrap().chunks.push(chunk),
+                Err(status) => {
+                    self.abort(id, owner)?;
+                    return Err(status);
+                }
+            }
+        }
+
+        let upload = self.uploads.remove(&id).unwrap();
+        let dump_id = self.next_dump;
+        self.next_dump = self.next_dump.wrapping_add(1).max(1);
+        self.insert(Dump {
+            id: dump_id,
+            time: upload.time,
+            pid: upload.pid,
+            component: upload.component,
+            reason: upload.reason,
+            size: upload.size,
+            chunks: upload.chunks,
+        });
+        if self.expire(now)? == 0 {
+            self.save()?;
+        }
+        Ok(dump_id)
+    }
+
+    pub fn abort(&mut self, id: u32, owner: u64) -> Result<(), i32> {
+        self.upload_of(id, owner)?;
+        let upload = self.uploads.remove(&id).unwrap();
+        for chunk in upload.chunks {
+            self.release_chunk(chunk);
+        }
+        Ok(())
+    }
+
+    /// Name the chunk holding `raw`, writing it unless it is stored already
+    fn put_chunk(&mut self, raw: &[u8]) -> Result<u32, i32> {
+        let digest = Sha256::digest(raw);
+        if let Some(&id) = self.by_digest.get(&digest) {
+            self.chunks.get_mut(&id).unwrap().refs += 1;
+            self.dedup_hits += 1;
+            return Ok(id);
+        }
+
+        let packed = orion_zram::compress(raw);
+        let compressed = packed.len() < raw.len();
+        let mut data = if compressed { packed } else { raw.to_vec() };
+        let stored = data.len() as u32;
+        let count = units(stored as u64);
+        let unit = loop {
+            if let Some(unit) = self.allocate(count) {
+                break unit;
+            }
+            // The oldest dumps make way; their space is reusable once an
+            // index no longer naming them is saved
+            if self.released.is_empty() && !self.evict_oldest() {
+                return Err(STATUS_ENOSPC);
+            }
+            self.save()?;
+        };
+        data.resize((count * UNIT_SIZE) as usize, 0);
+        if let Err(status) = self.volume.write(unit * UNIT_SIZE, &data) {
+            self.used_units -= count;
+            self.mark(unit, count, false);
+            return Err(status);
+        }
+
+        let id = self.next_chunk;
+        self.next_chunk += 1;
+        self.chunks.insert(id, Chunk { digest, unit, stored, raw: raw.len() as u32, compressed, refs: 1 });
+        self.by_digest.insert(digest, id);
+        Ok(id)
+    }
+
+    // ========================================
+    // Reads
+    // ========================================
+
+    fn load_chunk(&mut self, id: u32) -> Result<&[u8], i32> {
+        if self.cache.as_ref().is_none_or(|(cached, _)| *cached != id) {
+            let chunk = self.chunks.get(&id).ok_or(STATUS_EIO)?;
+            let mut data = vec![0u8; (units(chunk.stored as u64) * UNIT_SIZE) as usize];
+            self.volume.read(chunk.unit * UNIT_SIZE, &mut data)?;
+            data.truncate(chunk.stored as usize);
+            if chunk.compressed {
+                data = orion_zram::decompress(&data, chunk.raw as usize).map_err(|_| STATUS_EIO)?;
+            }
+            if data.len() != chunk.raw as usize || Sha256::digest(&data) != chunk.digest {
+                return Err(STATUS_EIO);
+            }
+            self.cache = Some((id, data));
+        }
+        Ok(&self.cache.as_ref().unwrap().1)
+    }
+
+    /// Up to `max` bytes of dump `id` from `offset`, short at the end of a
+    /// chunk and empty at the end of the dump
+    pub fn read(&mut self, id: u32, offset: u64, max: usize) -> Result<Vec<u8>, i32> {
+        let dump = self.dumps.get(&id).ok_or(STATUS_ENOENT)?;
+        if offset >= dump.size {
+            return Ok(Vec::new());
+        }
+        let chunk = dump.chunks[(offset / CHUNK_SIZE as u64) as usize];
+        let within = (offset % CHUNK_SIZE as u64) as usize;
+        let data = self.load_chunk(chunk)?;
+        let end = data.len().min(within.saturating_add(max));
+        Ok(data.get(within..end).ok_or(STATUS_EIO)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MemoryVolume {
+        data: Vec<u8>,
+    }
+
+    impl Volume for &mut MemoryVolume {
+        fn size(&self) -> u64 {
+            self.data.len() as u64
+        }
+
+        fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32> {
+            buffer.copy_from_slice(&self.data[offset as usize..offset as usize + buffer.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32> {
+            self.data[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), i32> {
+            Ok(())
+        }
+    }
+
+    /// Content that does not compress, different for every seed
+    fn noise(seed: u64, length: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e3779b97f4a7c15) | 1;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn store_dump<V: Volume>(store: &mut DumpStore<V>, component: &str, time: u64, data: &[u8]) -> u32 {
+        let upload = store.begin(7, 42, time, component, "fault").unwrap();
+        for piece in data.chunks(10_000) {
+            store.append(upload, 7, piece).unwrap();
+        }
+        store.commit(upload, 7, time).unwrap()
+    }
+
+    fn read_all<V: Volume>(store: &mut DumpStore<V>, id: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        loop {
+            let piece = store.read(id, data.len() as u64, 60_000).unwrap();
+            if piece.is_empty() {
+                return data;
+            }
+            data.extend_from_slice(&piece);
+        }
+    }
+
+    #[test]
+    fn test_stores_deduplicates_and_reopens() {
+        let mut volume = MemoryVolume { data: vec![0; (MIN_VOLUME_SIZE + 64 * UNIT_SIZE) as usize] };
+        let mut store = DumpStore::open(&mut volume).unwrap();
+
+        let mut first = vec![0x5a; 3 * CHUNK_SIZE];
+        first.extend_from_slice(&noise(1, CHUNK_SIZE + 100));
+        let id = store_dump(&mut store, "netd", 1_000, &first);
+        // The repeated chunk is stored once, and compressed
+        assert_eq!(store.stats().chunks, 3);
+        assert_eq!(store.stats().dedup_hits, 2);
+        assert!(store.stats().stored_bytes < first.len() as u64 / 2);
+        assert_eq!(read_all(&mut store, id), first);
+
+        // A second crash shares every chunk but the last
+        let mut second = first.clone();
+        second.truncate(4 * CHUNK_SIZE);
+        second.extend_from_slice(b"other tail");
+        let other = store_dump(&mut store, "netd", 2_000, &second);
+        assert_eq!(store.stats().chunks, 4);
+        store_dump(&mut store, "kernel", 1_500, b"panic");
+
+        let listed: Vec<u32> = store.list(Some("netd"), 0).iter().map(|dump| dump.id).collect();
+        assert_eq!(listed, [id, other]);
+        assert_eq!(store.list(None, 1_200).len(), 2);
+
+        let upload = store.begin(9, 0, 3_000, "fsd", "").unwrap();
+        assert_eq!(store.append(upload, 8, b"x"), Err(STATUS_EPERM));
+        store.append(upload, 9, &noise(2, 100)).unwrap();
+        drop(store);
+
+        // Uncommitted uploads are lost; the rest comes back intact
+        let mut store = DumpStore::open(&mut volume).unwrap();
+        assert_eq!(store.stats().dumps, 3);
+        assert_eq!(read_all(&mut store, other), second);
+        assert_eq!(store.stats().chunks, 5);
+        // Only the tail of the first dump was its own
+        store.remove(id).unwrap();
+        assert_eq!(store.stats().chunks, 4);
+        assert_eq!(store.remove(id), Err(STATUS_ENOENT));
+
+        // A torn index leaves the previous one in charge
+        let latest = (store.sequence % 2 * INDEX_UNITS * UNIT_SIZE) as usize;
+        drop(store);
+        volume.data[latest + INDEX_HEADER_SIZE] ^= 1;
+        let store = DumpStore::open(&mut volume).unwrap();
+        assert_eq!(store.stats().dumps, 3);
+    }
+
+    #[test]
+    fn test_retention_drops_the_oldest() {
+        let mut volume = MemoryVolume { data: vec![0; (MIN_VOLUME_SIZE + 16 * UNIT_SIZE) as usize] };
+        let mut store = DumpStore::open(&mut volume).unwrap();
+        let retention = Retention { max_dumps: 3, max_per_component: 2, max_bytes: 0, max_age_ns: 10_000 };
+        store.set_retention(retention, 0).unwrap();
+
+        let a = store_dump(&mut store, "a", 100, b"one");
+        store_dump(&mut store, "a", 200, b"two");
+        store_dump(&mut store, "a", 300, b"three");
+        assert!(store.dump(a).is_none());
+        store_dump(&mut store, "b", 400, b"four");
+        store_dump(&mut store, "b", 500, b"five");
+        assert_eq!(store.stats().dumps, 3);
+        assert_eq!(store.list(Some("a"), 0).len(), 1);
+
+        assert_eq!(store.expire(10_450).unwrap(), 2);
+        let last = store.list(None, 0)[0].id;
+        assert_eq!(store.dump(last).unwrap().time, 500);
+        assert_eq!(store.stats().evicted, 4);
+        store.remove(last).unwrap();
+
+        // Running out of space drops the oldest dumps to make room
+        store.set_retention(Retention { max_age_ns: 0, ..retention }, 0).unwrap();
+        let space = store.stats().capacity as usize;
+        let old = store_dump(&mut store, "c", 600, &noise(3, space / 2));
+        let kept = store_dump(&mut store, "c", 700, &noise(4, space / 2));
+        let new = store_dump(&mut store, "c", 800, &noise(5, space / 2));
+        assert!(store.dump(old).is_none());
+        assert_eq!(store.list(Some("c"), 0).iter().map(|dump| dump.id).collect::<Vec<_>>(), [kept, new]);
+        assert_eq!(store.stats().evicted, 5);
+        assert_eq!(read_all(&mut store, new), noise(5, space / 2));
+
+        let upload = store.begin(7, 0, 900, "d", "").unwrap();
+        assert_eq!(store.append(upload, 7, &noise(6, space + CHUNK_SIZE)), Err(STATUS_ENOSPC));
+        assert_eq!(
+            store.set_retention(Retention { max_per_component: 4, max_dumps: 2, ..retention }, 0),
+            Err(STATUS_EINVAL)
+        );
+    }
+}
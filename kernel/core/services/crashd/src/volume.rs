@@ -0,0 +1,118 @@
+/*
+ * Orion Operating System - Crash Volume
+ *
+ * The logical volume the store lives on, reached through the volume
+ * requests of the LVM control protocol. The volume is reserved for crash
+ * dumps: nothing else mounts or writes it, and the store formats it when
+ * it finds no index on it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+use crate::protocol::{STATUS_EINVAL, STATUS_EIO, STATUS_OK};
+use crate::store::{Volume, UNIT_SIZE};
+
+/// Logical volume reserved for the dumps
+pub const CRASH_VOLUME: &str = "crash";
+
+/// Largest chunk moved by one IPC call
+const MAX_TRANSFER: usize = 64 * 1024;
+
+// LVM control requests (see drivers/block/src/lvm.rs)
+const LVM_CTRL_VOLUME_INFO: u32 = 5;
+const LVM_CTRL_VOLUME_READ: u32 = 6;
+const LVM_CTRL_VOLUME_WRITE: u32 = 7;
+const LVM_CTRL_VOLUME_FLUSH: u32 = 8;
+
+/// Issue a request and split the reply
+fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = channel.call(request).map_err(|_| STATUS_EIO)?;
+    if response.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+pub struct LvmVolume {
+    channel: IpcChannel,
+    name: String,
+    size: u64,
+}
+
+impl LvmVolume {
+    pub fn open(mut channel: IpcChannel, name: &str) -> Result<Self, i32> {
+        let mut request = LVM_CTRL_VOLUME_INFO.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        let payload = call(&mut channel, &request)?;
+        let size = payload.get(0..8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap())).ok_or(STATUS_EIO)?;
+        let block_size =
+            payload.get(8..12).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap())).ok_or(STATUS_EIO)?;
+        // The store writes whole units
+        if !block_size.is_power_of_two() || !UNIT_SIZE.is_multiple_of(block_size as u64) {
+            return Err(STATUS_EINVAL);
+        }
+        Ok(Self { channel, name: String::from(name), size: size - size % UNIT_SIZE })
+    }
+
+    fn request(&self, opcode: u32, offset: u64) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        put_string(&mut request, &self.name);
+        request.extend_from_slice(&offset.to_le_bytes());
+        request
+    }
+}
+
+impl Volume for LvmVolume {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        let mut position = offset;
+        for chunk in buffer.chunks_mut(MAX_TRANSFER) {
+            let mut request = self.request(LVM_CTRL_VOLUME_READ, position);
+            request.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            let data = call(&mut self.channel, &request)?;
+            if data.len() != chunk.len() {
+                return Err(STATUS_EIO);
+            }
+            chunk.copy_from_slice(&data);
+            position += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32> {
+        let mut position = offset;
+        for chunk in data.chunks(MAX_TRANSFER) {
+            let mut request = self.request(LVM_CTRL_VOLUME_WRITE, position);
+            request.extend_from_slice(chunk);
+            call(&mut self.channel, &request)?;
+            position += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), i32> {
+        let mut request = LVM_CTRL_VOLUME_FLUSH.to_le_bytes().to_vec();
+        put_string(&mut request, &self.name);
+        call(&mut self.channel, &request).map(|_| ())
+    }
+}
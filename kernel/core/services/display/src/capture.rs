@@ -0,0 +1,227 @@
+/*
+ * Orion Operating System - Display Capture
+ *
+ * Screenshots and screencasts of an output or a single window, written
+ * into a shared memory buffer supplied by the client. The buffer starts
+ * with a frame header and holds the pixels at FRAME_PIXELS_OFFSET:
+ *
+ *   0   magic:u32 "OCAP"     4   width:u32      8   height:u32
+ *   12  stride:u32 (pixels)  16  sequence:u64   24  timestamp:u64 (ns)
+ *   32  count:u32            36  flags:u32      40  rects: count x
+ *                                                   (x:i32 y:i32 w:u32 h:u32)
+ *
+ * A stream keeps the damage of its target and copies only the damaged
+ * rectangles into the buffer, listing them in the header. The first frame
+ * and every frame after a resize of the target cover the whole surface.
+ * A frame is produced when there is damage, the client released the
+ * previous frame, and the frame interval derived from max_fps has passed.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::compose::{Cursor, Pixels, PixelsMut};
+use crate::damage::{Damage, Rect, MAX_DAMAGE_RECTS};
+
+pub const FRAME_MAGIC: u32 = 0x5041_434F; // "OCAP"
+pub const FRAME_HEADER_SIZE: usize = 40 + MAX_DAMAGE_RECTS * 16;
+/// Pixels start on their own page so clients can map them separately
+pub const FRAME_PIXELS_OFFSET: usize = 4096;
+
+// Capture flags
+/// Blend the cursor into the captured pixels
+pub const CAPTURE_CURSOR: u32 = 1 << 0;
+
+/// Capture streams across all clients
+pub const MAX_STREAMS: usize = 16;
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Output(u32),
+    Window(u32),
+}
+
+/// Buffer size needed to capture a `width` x `height` surface
+pub fn buffer_size(width: u32, height: u32) -> usize {
+    FRAME_PIXELS_OFFSET + width as usize * height as usize * 4
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInfo {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub flags: u32,
+}
+
+/// Copy `rects` of `source` into the frame buffer and fill in its header.
+/// `cursor` is drawn with the target placed at `origin` on the output.
+pub fn write_frame(
+    header: &mut [u8],
+    pixels: &mut [u32],
+    source: &Pixels,
+    rects: &[Rect],
+    cursor: Option<(&Cursor, i32, i32)>,
+    info: FrameInfo,
+) -> bool {
+    let bounds = source.bounds();
+    let Some(mut frame) = PixelsMut::new(pixels, bounds.width, bounds.height, bounds.width) else {
+        return false;
+    };
+    if header.len() < FRAME_HEADER_SIZE || rects.len() > MAX_DAMAGE_RECTS {
+        return false;
+    }
+    for rect in rects {
+        frame.blit(source, 0, 0, *rect);
+        if let Some((cursor, origin_x, origin_y)) = cursor {
+            cursor.draw(&mut frame, origin_x, origin_y, *rect);
+        }
+    }
+
+    let mut put = |offset: usize, bytes: &[u8]| header[offset..offset + bytes.len()].copy_from_slice(bytes);
+    put(0, &FRAME_MAGIC.to_le_bytes());
+    put(4, &bounds.width.to_le_bytes());
+    put(8, &bounds.height.to_le_bytes());
+    put(12, &bounds.width.to_le_bytes());
+    put(16, &info.sequence.to_le_bytes());
+    put(24, &info.timestamp.to_le_bytes());
+    put(32, &(rects.len() as u32).to_le_bytes());
+    put(36, &info.flags.to_le_bytes());
+    for (index, rect) in rects.iter().enumerate() {
+        let offset = 40 + index * 16;
+        put(offset, &rect.x.to_le_bytes());
+        put(offset + 4, &rect.y.to_le_bytes());
+        put(offset + 8, &rect.width.to_le_bytes());
+        put(offset + 12, &rect.height.to_le_bytes());
+    }
+    true
+}
+
+pub struct CaptureStream {
+    pub owner: u64,
+    pub target: Target,
+    pub flags: u32,
+    /// Shared buffer mapping
+    pub mapping: u64,
+    pub size: usize,
+    /// Surface size of the last frame
+    pub width: u32,
+    pub height: u32,
+    interval_ns: u64,
+    damage: Damage,
+    last_frame_ns: Option<u64>,
+    sequence: u64,
+    released: bool,
+}
+
+impl CaptureStream {
+    /// `max_fps` 0 delivers frames as fast as the client releases them
+    pub fn new(owner: u64, target: Target, flags: u32, max_fps: u32, mapping: u64, size: usize) -> Self {
+        Self {
+            owner,
+            target,
+            flags,
+            mapping,
+            size,
+            width: 0,
+            height: 0,
+            interval_ns: if max_fps == 0 { 0 } else { NS_PER_SEC / max_fps as u64 },
+            damage: Damage::new(),
+            last_frame_ns: None,
+            sequence: 0,
+            released: true,
+        }
+    }
+
+    pub fn wants_cursor(&self) -> bool {
+        self.flags & CAPTURE_CURSOR != 0
+    }
+
+    pub fn add_damage(&mut self, rect: Rect) {
+        self.damage.add(rect);
+    }
+
+    pub fn release(&mut self) {
+        self.released = true;
+    }
+
+    /// Whether a frame should be produced at `now`
+    pub fn ready(&self, now: u64) -> bool {
+        let due = self.last_frame_ns.is_none_or(|last| now >= last.saturating_add(self.interval_ns));
+        self.released && due && (self.last_frame_ns.is_none() || !self.damage.is_empty())
+    }
+
+    /// Rectangles of the next frame of a `width` x `height` target, or None
+    /// when the buffer cannot hold it
+    pub fn begin_frame(&mut self, width: u32, height: u32, now: u64) -> Option<(u64, Vec<Rect>)> {
+        if buffer_size(width, height) > self.size {
+            return None;
+        }
+        let bounds = Rect::new(0, 0, width, height);
+        let rects = if self.last_frame_ns.is_none() || (width, height) != (self.width, self.height) {
+            self.damage.take(&bounds);
+            vec![bounds]
+        } else {
+            self.damage.take(&bounds)
+        };
+        self.width = width;
+        self.height = height;
+        self.last_frame_ns = Some(now);
+        self.released = false;
+        self.sequence += 1;
+        Some((self.sequence, rects))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paces_frames() {
+        let mut stream = CaptureStream::new(1, Target::Output(1), 0, 10, 0, buffer_size(4, 4));
+        assert!(stream.ready(0));
+        assert_eq!(stream.begin_frame(4, 4, 0), Some((1, vec![Rect::new(0, 0, 4, 4)])));
+
+        // Damage waits for both the release and the frame interval
+        stream.add_damage(Rect::new(1, 1, 1, 1));
+        assert!(!stream.ready(200_000_000));
+        stream.release();
+        assert!(!stream.ready(50_000_000));
+        assert!(stream.ready(100_000_000));
+        assert_eq!(stream.begin_frame(4, 4, 100_000_000), Some((2, vec![Rect::new(1, 1, 1, 1)])));
+
+        stream.release();
+        assert!(!stream.ready(300_000_000));
+        // A resized target gets a full frame, a buffer too small none
+        stream.add_damage(Rect::new(0, 0, 1, 1));
+        assert_eq!(stream.begin_frame(2, 2, 300_000_000), Some((3, vec![Rect::new(0, 0, 2, 2)])));
+        assert_eq!(stream.begin_frame(8, 8, 400_000_000), None);
+    }
+
+    #[test]
+    fn test_writes_damaged_rects() {
+        let surface = [1, 2, 3, 4];
+        let source = Pixels::new(&surface, 2, 2, 2).unwrap();
+        let cursor = Cursor { x: 5, y: 5, hot_x: 0, hot_y: 0, width: 1, height: 1, image: vec![0xffff_ffff] };
+        let mut header = vec![0u8; FRAME_HEADER_SIZE];
+        let mut pixels = vec![0u32; 4];
+
+        // Target at (4, 4) on the output puts the cursor on its bottom-right pixel
+        let rects = [Rect::new(1, 0, 1, 2)];
+        let info = FrameInfo { sequence: 7, timestamp: 99, flags: CAPTURE_CURSOR };
+        assert!(write_frame(&mut header, &mut pixels, &source, &rects, Some((&cursor, 4, 4)), info));
+        assert_eq!(pixels, [0, 2, 0, 0xffff_ffff]);
+        assert_eq!(&header[0..4], b"OCAP");
+        assert_eq!(&header[16..24], &7u64.to_le_bytes());
+        assert_eq!(&header[32..36], &1u32.to_le_bytes());
+        assert_eq!(&header[40..56], &[1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,197 @@
+/*
+ * Orion Operating System - Display Composition
+ *
+ * Pixel operations of the display server on 32-bit ARGB surfaces: window
+ * contents are copied opaque onto outputs, the cursor is alpha blended. A
+ * surface is a pixel slice with a row stride, so output framebuffers,
+ * window buffers and capture buffers are all handled the same way.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use crate::damage::Rect;
+
+/// Colour of output areas no window covers
+pub const BACKGROUND: u32 = 0xff20_2020;
+
+/// Largest cursor image, in pixels per side
+pub const MAX_CURSOR_SIZE: u32 = 64;
+
+pub struct Pixels<'a> {
+    data: &'a [u32],
+    width: u32,
+    height: u32,
+    stride: usize,
+}
+
+impl<'a> Pixels<'a> {
+    /// None when `data` is too short for the geometry
+    pub fn new(data: &'a [u32], width: u32, height: u32, stride: u32) -> Option<Self> {
+        if stride < width || (height > 0 && data.len() < (height as usize - 1) * stride as usize + width as usize) {
+            return None;
+        }
+        Some(Self { data, width, height, stride: stride as usize })
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    fn row(&self, x: i32, y: i32, width: u32) -> &[u32] {
+        let start = y as usize * self.stride + x as usize;
+        &self.data[start..start + width as usize]
+    }
+}
+
+pub struct PixelsMut<'a> {
+    data: &'a mut [u32],
+    width: u32,
+    height: u32,
+    stride: usize,
+}
+
+impl<'a> PixelsMut<'a> {
+    pub fn new(data: &'a mut [u32], width: u32, height: u32, stride: u32) -> Option<Self> {
+        if stride < width || (height > 0 && data.len() < (height as usize - 1) * stride as usize + width as usize) {
+            return None;
+        }
+        Some(Self { data, width, height, stride: stride as usize })
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    fn row_mut(&mut self, x: i32, y: i32, width: u32) -> &mut [u32] {
+        let start = y as usize * self.stride + x as usize;
+        &mut self.data[start..start + width as usize]
+    }
+
+    pub fn fill(&mut self, rect: Rect, value: u32) {
+        if let Some(rect) = rect.intersect(&self.bounds()) {
+            for y in rect.y..rect.y + rect.height as i32 {
+                self.row_mut(rect.x, y, rect.width).fill(value);
+            }
+        }
+    }
+
+    /// Copy `source` placed at (x, y) into `clip`
+    pub fn blit(&mut self, source: &Pixels, x: i32, y: i32, clip: Rect) {
+        let Some(area) =
+            clip.intersect(&self.bounds()).and_then(|area| area.intersect(&source.bounds().translate(x, y)))
+        else {
+            return;
+        };
+        for row in area.y..area.y + area.height as i32 {
+            let from = source.row(area.x - x, row - y, area.width);
+            self.row_mut(area.x, row, area.width).copy_from_slice(from);
+        }
+    }
+
+    /// Alpha blend `source` placed at (x, y) into `clip`
+    pub fn blend(&mut self, source: &Pixels, x: i32, y: i32, clip: Rect) {
+        let Some(area) =
+            clip.intersect(&self.bounds()).and_then(|area| area.intersect(&source.bounds().translate(x, y)))
+        else {
+            return;
+        };
+        for row in area.y..area.y + area.height as i32 {
+            let from = source.row(area.x - x, row - y, area.width);
+            for (to, &pixel) in self.row_mut(area.x, row, area.width).iter_mut().zip(from) {
+                *to = blend_pixel(*to, pixel);
+            }
+        }
+    }
+}
+
+/// `over` with straight alpha onto an opaque pixel
+fn blend_pixel(below: u32, above: u32) -> u32 {
+    let alpha = above >> 24;
+    match alpha {
+        0 => below,
+        255 => above,
+        _ => {
+            let mix = |shift: u32| {
+                let top = (above >> shift) & 0xff;
+                let bottom = (below >> shift) & 0xff;
+                ((top * alpha + bottom * (255 - alpha) + 127) / 255) << shift
+            };
+            0xff00_0000 | mix(16) | mix(8) | mix(0)
+        }
+    }
+}
+
+/// Pointer image of an output; the hot spot sits at (x, y)
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub x: i32,
+    pub y: i32,
+    pub hot_x: u32,
+    pub hot_y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub image: Vec<u32>,
+}
+
+impl Cursor {
+    /// Area covered on the output
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x - self.hot_x as i32, self.y - self.hot_y as i32, self.width, self.height)
+    }
+
+    /// Blend the cursor into `target`, whose top-left corner sits at
+    /// (`origin_x`, `origin_y`) on the output
+    pub fn draw(&self, target: &mut PixelsMut, origin_x: i32, origin_y: i32, clip: Rect) {
+        if let Some(image) = Pixels::new(&self.image, self.width, self.height, self.width) {
+            let area = self.rect().translate(-origin_x, -origin_y);
+            target.blend(&image, area.x, area.y, clip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_blits_clipped() {
+        let window = [1, 2, 3, 4, 5, 6];
+        let source = Pixels::new(&window, 3, 2, 3).unwrap();
+        let mut output = vec![0u32; 4 * 3];
+        let mut target = PixelsMut::new(&mut output, 4, 3, 4).unwrap();
+
+        // Half the window hangs off the left edge, the clip drops its last row
+        target.blit(&source, -1, 1, Rect::new(0, 0, 4, 2));
+        assert_eq!(output, [0, 0, 0, 0, 2, 3, 0, 0, 0, 0, 0, 0]);
+        assert!(Pixels::new(&window, 3, 3, 3).is_none());
+    }
+
+    #[test]
+    fn test_blends_cursor() {
+        let cursor = Cursor {
+            x: 2,
+            y: 2,
+            hot_x: 1,
+            hot_y: 1,
+            width: 2,
+            height: 2,
+            image: vec![0xff00_00ff, 0x0000_0000, 0x80ff_ffff, 0xff00_ff00],
+        };
+        assert_eq!(cursor.rect(), Rect::new(1, 1, 2, 2));
+
+        let mut frame = vec![0xff00_0000u32; 9];
+        let mut target = PixelsMut::new(&mut frame, 3, 3, 3).unwrap();
+        cursor.draw(&mut target, 0, 0, Rect::new(0, 0, 3, 3));
+        assert_eq!(frame[4], 0xff00_00ff);
+        assert_eq!(frame[5], 0xff00_0000);
+        assert_eq!(frame[7], 0xff80_8080);
+        assert_eq!(frame[8], 0xff00_ff00);
+    }
+}
@@ -0,0 +1,164 @@
+/*
+ * Orion Operating System - Display Damage Tracking
+ *
+ * Rectangles of a surface that changed since they were last consumed.
+ * Overlapping rectangles are merged as they arrive; past MAX_DAMAGE_RECTS
+ * the region collapses to its bounding box, which over-reports but never
+ * loses an update.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+/// Rectangles reported per frame
+pub const MAX_DAMAGE_RECTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && (x as i64) < self.right() && (y as i64) < self.bottom()
+    }
+
+    pub fn translate(&self, dx: i32, dy: i32) -> Self {
+        Self { x: self.x.saturating_add(dx), y: self.y.saturating_add(dy), ..*self }
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= left as i64 || bottom <= top as i64 {
+            return None;
+        }
+        Some(Rect::new(left, top, (right - left as i64) as u32, (bottom - top as i64) as u32))
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        let left = self.x.min(other.x);
+        let top = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Rect::new(left, top, (right - left as i64) as u32, (bottom - top as i64) as u32)
+    }
+
+    // Overlapping or sharing an edge
+    fn touches(&self, other: &Rect) -> bool {
+        self.x as i64 <= other.right()
+            && other.x as i64 <= self.right()
+            && self.y as i64 <= other.bottom()
+            && other.y as i64 <= self.bottom()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Damage {
+    rects: Vec<Rect>,
+}
+
+impl Damage {
+    pub fn new() -> Self {
+        Self { rects: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn add(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        // Absorb every rectangle the new one touches, then repeat with the
+        // grown rectangle until it stops growing
+        let mut merged = rect;
+        loop {
+            let before = self.rects.len();
+            self.rects.retain(|existing| {
+                if existing.touches(&merged) {
+                    merged = merged.union(existing);
+                    false
+                } else {
+                    true
+                }
+            });
+            if self.rects.len() == before {
+                break;
+            }
+        }
+        self.rects.push(merged);
+
+        if self.rects.len() > MAX_DAMAGE_RECTS {
+            let bounds = self.rects.iter().skip(1).fold(self.rects[0], |bounds, rect| bounds.union(rect));
+            self.rects.clear();
+            self.rects.push(bounds);
+        }
+    }
+
+    /// Hand out the accumulated rectangles, clipped to `bounds`
+    pub fn take(&mut self, bounds: &Rect) -> Vec<Rect> {
+        let rects = self.rects.iter().filter_map(|rect| rect.intersect(bounds)).collect();
+        self.rects.clear();
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merges_touching_rects() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 10, 10));
+        damage.add(Rect::new(100, 100, 5, 5));
+        // Bridges both rectangles
+        damage.add(Rect::new(10, 0, 95, 100));
+        damage.add(Rect::new(0, 0, 0, 50));
+
+        let bounds = Rect::new(0, 0, 102, 102);
+        assert_eq!(damage.take(&bounds), [Rect::new(0, 0, 102, 102)]);
+        assert!(damage.is_empty());
+    }
+
+    #[test]
+    fn test_collapses_to_bounding_box() {
+        let mut damage = Damage::new();
+        for index in 0..=MAX_DAMAGE_RECTS as i32 {
+            damage.add(Rect::new(index * 10, index * 10, 2, 2));
+        }
+        let max = MAX_DAMAGE_RECTS as i32 * 10;
+        assert_eq!(damage.take(&Rect::new(-5, -5, 1000, 1000)), [Rect::new(0, 0, max as u32 + 2, max as u32 + 2)]);
+
+        damage.add(Rect::new(-4, -4, 8, 8));
+        assert_eq!(damage.take(&Rect::new(0, 0, 100, 100)), [Rect::new(0, 0, 4, 4)]);
+    }
+}
@@ -0,0 +1,757 @@
+/*
+ * Orion Operating System - Display Server
+ *
+ * Owns the framebuffers of the outputs and composes client windows onto
+ * them. The GPU driver registers each output with a shared mapping of its
+ * scanout framebuffer; clients create windows backed by their own shared
+ * buffers and commit the damaged rectangles after drawing. Windows stack
+ * in creation order and are copied opaque; the cursor is kept out of the
+ * framebuffer (the GPU draws it) and only blended into captures.
+ *
+ * Screenshots and screencasts of an output or a window are delivered into
+ * client buffers (see capture.rs). Pointer and key input reported by input
+ * sources is routed to the window under the pointer and the focused
+ * window; focus changes are reported to the keymap server so it applies
+ * the layout of the focused window. Windows exchange data through the
+ * clipboard, the primary selection and drag-and-drop (see selection.rs).
+ * Capturing needs the read right on the display capability,
+ * drawing the write right, registering outputs and injecting input the
+ * admin right.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+use orion_sys::clock_get;
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod capture;
+mod compose;
+mod damage;
+mod protocol;
+mod selection;
+
+use capture::{
+    buffer_size, write_frame, CaptureStream, FrameInfo, Target, CAPTURE_CURSOR, FRAME_HEADER_SIZE, FRAME_PIXELS_OFFSET,
+    MAX_STREAMS,
+};
+use compose::{Cursor, Pixels, PixelsMut, BACKGROUND};
+use damage::Rect;
+use protocol::*;
+use selection::{Selection, Selections};
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 4_000_000;
+
+const MAX_OUTPUTS: usize = 8;
+const MAX_WINDOWS: usize = 256;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+struct Output {
+    /// Framebuffer mapping
+    mapping: u64,
+    size: usize,
+    width: u32,
+    height: u32,
+    stride: u32,
+    cursor: Option<Cursor>,
+}
+
+impl Output {
+    fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    // The mapping stays in place for the lifetime of the output
+    fn pixels(&self) -> Option<Pixels<'_>> {
+        let data = unsafe { core::slice::from_raw_parts(self.mapping as *const u32, self.size / 4) };
+        Pixels::new(data, self.width, self.height, self.stride)
+    }
+
+    fn pixels_mut(&mut self) -> Option<PixelsMut<'_>> {
+        let data = unsafe { core::slice::from_raw_parts_mut(self.mapping as *mut u32, self.size / 4) };
+        PixelsMut::new(data, self.width, self.height, self.stride)
+    }
+}
+
+struct Window {
+    owner: u64,
+    output: u32,
+    /// Position and size on the output
+    area: Rect,
+    mapping: u64,
+    size: usize,
+}
+
+impl Window {
+    // The mapping stays in place until the window is destroyed
+    fn pixels(&self) -> Option<Pixels<'_>> {
+        let data = unsafe { core::slice::from_raw_parts(self.mapping as *const u32, self.size / 4) };
+        Pixels::new(data, self.area.width, self.area.height, self.area.width)
+    }
+}
+
+/// Pixels of a capture target, the cursor of its output and the target
+/// origin on that output
+fn capture_source<'a>(
+    outputs: &'a BTreeMap<u32, Output>,
+    windows: &'a BTreeMap<u32, Window>,
+    target: Target,
+) -> Option<(Pixels<'a>, Option<&'a Cursor>, i32, i32)> {
+    match target {
+        Target::Output(id) => {
+            let output = outputs.get(&id)?;
+            Some((output.pixels()?, output.cursor.as_ref(), 0, 0))
+        }
+        Target::Window(id) => {
+            let window = windows.get(&id)?;
+            let cursor = outputs.get(&window.output)?.cursor.as_ref();
+            Some((window.pixels()?, cursor, window.area.x, window.area.y))
+        }
+    }
+}
+
+/// Header and pixel area of a mapped capture buffer of at least
+/// FRAME_PIXELS_OFFSET bytes
+unsafe fn frame_buffer<'a>(mapping: u64, size: usize) -> (&'a mut [u8], &'a mut [u32]) {
+    let header = core::slice::from_raw_parts_mut(mapping as *mut u8, FRAME_HEADER_SIZE);
+    let pixels = core::slice::from_raw_parts_mut(
+        (mapping + FRAME_PIXELS_OFFSET as u64) as *mut u32,
+        (size - FRAME_PIXELS_OFFSET) / 4,
+    );
+    (header, pixels)
+}
+
+/// Map the shared buffer behind `capability`, keeping it only if it holds
+/// at least `minimum` bytes
+fn map_buffer(capability: u64, minimum: usize) -> Result<(u64, usize), i32> {
+    let (mapping, size) = orion_sys::shm_attach(capability, 0, 0).map_err(|_| STATUS_EPERM)?;
+    if size < minimum {
+        let _ = orion_sys::shm_detach(mapping);
+        return Err(STATUS_EINVAL);
+    }
+    Ok((mapping, size))
+}
+
+struct DisplayServer {
+    outputs: BTreeMap<u32, Output>,
+    windows: BTreeMap<u32, Window>,
+    streams: BTreeMap<u32, CaptureStream>,
+    selections: Selections,
+    /// Window with the keyboard focus
+    focus: Option<u32>,
+    buttons: u32,
+    next_id: u32,
+    keymap: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl DisplayServer {
+    fn new() -> Self {
+        Self {
+            outputs: BTreeMap::new(),
+            windows: BTreeMap::new(),
+            streams: BTreeMap::new(),
+            selections: Selections::new(),
+            focus: None,
+            buttons: 0,
+            next_id: 1,
+            keymap: IpcChannel::connect("keymap"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            self.deliver_frames(monotonic_ns());
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    // Window ids keep growing so the map order is the stacking order
+    fn allocate_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Recompose `rect` of an output from the windows covering it
+    fn redraw(&mut self, output_id: u32, rect: Rect) {
+        let Some(output) = self.outputs.get_mut(&output_id) else {
+            return;
+        };
+        let Some(area) = rect.intersect(&output.bounds()) else {
+            return;
+        };
+        let Some(mut target) = output.pixels_mut() else {
+            return;
+        };
+        target.fill(area, BACKGROUND);
+        for window in self.windows.values().filter(|window| window.output == output_id) {
+            if let Some(source) = window.pixels() {
+                target.blit(&source, window.area.x, window.area.y, area);
+            }
+        }
+        self.damage(Target::Output(output_id), area, false);
+    }
+
+    fn damage(&mut self, target: Target, rect: Rect, cursor_only: bool) {
+        for stream in self.streams.values_mut() {
+            if stream.target == target && (!cursor_only || stream.wants_cursor()) {
+                stream.add_damage(rect);
+            }
+        }
+    }
+
+    /// Cursor moved over `rect` of an output: only captures showing it change
+    fn damage_cursor(&mut self, output_id: u32, rect: Rect) {
+        self.damage(Target::Output(output_id), rect, true);
+        let covered: Vec<(u32, Rect)> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| window.output == output_id)
+            .filter_map(|(&id, window)| {
+                rect.intersect(&window.area).map(|area| (id, area.translate(-window.area.x, -window.area.y)))
+            })
+            .collect();
+        for (id, area) in covered {
+            self.damage(Target::Window(id), area, true);
+        }
+    }
+
+    fn end_streams(&mut self, target: Target) {
+        let ended: Vec<u32> =
+            self.streams.iter().filter(|(_, stream)| stream.target == target).map(|(&id, _)| id).collect();
+        for id in ended {
+            if let Some(stream) = self.streams.remove(&id) {
+                let _ = orion_sys::shm_detach(stream.mapping);
+                self.ipc_channel.send(stream.owner, &ended_event(id));
+            }
+        }
+    }
+
+    fn deliver_frames(&mut self, now: u64) {
+        let mut too_small = Vec::new();
+        for (&id, stream) in self.streams.iter_mut() {
+            if !stream.ready(now) {
+                continue;
+            }
+            let Some((source, cursor, origin_x, origin_y)) =
+                capture_source(&self.outputs, &self.windows, stream.target)
+            else {
+                continue;
+            };
+            let bounds = source.bounds();
+            let Some((sequence, rects)) = stream.begin_frame(bounds.width, bounds.height, now) else {
+                // The target grew past the buffer
+                too_small.push(stream.target);
+                continue;
+            };
+            let cursor = cursor.filter(|_| stream.wants_cursor()).map(|cursor| (cursor, origin_x, origin_y));
+            let (header, pixels) = unsafe { frame_buffer(stream.mapping, stream.size) };
+            let info = FrameInfo { sequence, timestamp: now, flags: stream.flags };
+            if write_frame(header, pixels, &source, &rects, cursor, info) {
+                self.ipc_channel.send(stream.owner, &frame_event(id, sequence));
+            }
+        }
+        for target in too_small {
+            self.end_streams(target);
+        }
+    }
+
+    fn target_exists(&self, target: Target) -> bool {
+        match target {
+            Target::Output(id) => self.outputs.contains_key(&id),
+            Target::Window(id) => self.windows.contains_key(&id),
+        }
+    }
+
+    fn register_output(&mut self, framebuffer: u64, width: u32, height: u32, stride: u32) -> Result<u32, i32> {
+        if self.outputs.len() >= MAX_OUTPUTS {
+            return Err(STATUS_ENOSPC);
+        }
+        if width == 0 || height == 0 || stride < width {
+            return Err(STATUS_EINVAL);
+        }
+        let (mapping, size) = map_buffer(framebuffer, stride as usize * height as usize * 4)?;
+        let id = self.allocate_id();
+        let output = Output { mapping, size, width, height, stride, cursor: None };
+        self.outputs.insert(id, output);
+        self.redraw(id, Rect::new(0, 0, width, height));
+        Ok(id)
+    }
+
+    fn create_window(&mut self, sender: u64, output: u32, area: Rect, buffer: u64) -> Result<u32, i32> {
+        if !self.outputs.contains_key(&output) {
+            return Err(STATUS_ENOENT);
+        }
+        if self.windows.len() >= MAX_WINDOWS {
+            return Err(STATUS_ENOSPC);
+        }
+        if area.is_empty() {
+            return Err(STATUS_EINVAL);
+        }
+        let (mapping, size) = map_buffer(buffer, area.width as usize * area.height as usize * 4)?;
+        let id = self.allocate_id();
+        self.windows.insert(id, Window { owner: sender, output, area, mapping, size });
+        self.redraw(output, area);
+        Ok(id)
+    }
+
+    fn commit_window(&mut self, sender: u64, id: u32, rects: Vec<Rect>) -> Result<(), i32> {
+        let (output, area) = match self.windows.get(&id) {
+            Some(window) if window.owner == sender => (window.output, window.area),
+            _ => return Err(STATUS_ENOENT),
+        };
+        let bounds = Rect::new(0, 0, area.width, area.height);
+        let rects = if rects.is_empty() { alloc::vec![bounds] } else { rects };
+        for rect in rects.iter().filter_map(|rect| rect.intersect(&bounds)) {
+            self.damage(Target::Window(id), rect, false);
+            self.redraw(output, rect.translate(area.x, area.y));
+        }
+        Ok(())
+    }
+
+    fn destroy_window(&mut self, sender: u64, id: u32) -> Result<(), i32> {
+        match self.windows.get(&id) {
+            Some(window) if window.owner == sender => {}
+            _ => return Err(STATUS_ENOENT),
+        }
+        let window = self.windows.remove(&id).unwrap();
+        let _ = orion_sys::shm_detach(window.mapping);
+        if self.focus == Some(id) {
+            self.focus = None;
+        }
+        let _ = self.keymap.post(&keymap_forget(id));
+        let (offers, transfers) = self.selections.window_destroyed(id);
+        for offer in offers {
+            self.notify_selection(offer.selection);
+        }
+        for transfer in transfers {
+            self.end_transfer(&transfer, &[transfer.source, transfer.receiver]);
+        }
+        self.end_streams(Target::Window(id));
+        self.redraw(window.output, window.area);
+        Ok(())
+    }
+
+    fn set_cursor(
+        &mut self,
+        output_id: u32,
+        hot_x: u32,
+        hot_y: u32,
+        width: u32,
+        height: u32,
+        image: Vec<u32>,
+    ) -> Result<(), i32> {
+        let output = self.outputs.get_mut(&output_id).ok_or(STATUS_ENOENT)?;
+        if width != 0 && (hot_x >= width || hot_y >= height) {
+            return Err(STATUS_EINVAL);
+        }
+        let (x, y) = output.cursor.as_ref().map_or((0, 0), |cursor| (cursor.x, cursor.y));
+        let old = output.cursor.take().map(|cursor| cursor.rect());
+        if width != 0 && height != 0 {
+            output.cursor = Some(Cursor { x, y, hot_x, hot_y, width, height, image });
+        }
+        let new = output.cursor.as_ref().map(|cursor| cursor.rect());
+        for rect in [old, new].into_iter().flatten() {
+            self.damage_cursor(output_id, rect);
+        }
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, output_id: u32, x: i32, y: i32) -> Result<(), i32> {
+        let output = self.outputs.get_mut(&output_id).ok_or(STATUS_ENOENT)?;
+        let Some(cursor) = output.cursor.as_mut() else {
+            return Ok(());
+        };
+        let old = cursor.rect();
+        cursor.x = x;
+        cursor.y = y;
+        let new = cursor.rect();
+        self.damage_cursor(output_id, old);
+        self.damage_cursor(output_id, new);
+        Ok(())
+    }
+
+    fn pointer_input(&mut self, output_id: u32, x: i32, y: i32, buttons: u32) -> Result<(), i32> {
+        self.move_cursor(output_id, x, y)?;
+        let pressed = buttons & !self.buttons != 0;
+        let released = self.buttons != 0 && buttons == 0;
+        self.buttons = buttons;
+
+        // Topmost window under the pointer
+        let under = self
+            .windows
+            .iter()
+            .rev()
+            .find(|(_, window)| window.output == output_id && window.area.contains(x, y))
+            .map(|(&id, window)| (id, window.owner, window.area));
+        if let Some((id, owner, area)) = under {
+            if pressed && self.focus != Some(id) {
+                self.focus = Some(id);
+                let _ = self.keymap.post(&keymap_focus(id, owner));
+                self.notify_selection(Selection::Clipboard);
+                self.notify_selection(Selection::Primary);
+            }
+            self.ipc_channel.send(owner, &pointer_event(id, x - area.x, y - area.y, buttons));
+        }
+        if released {
+            self.end_drag(under.map(|(id, owner, area)| (id, owner, x - area.x, y - area.y)));
+        }
+        Ok(())
+    }
+
+    fn key_input(&mut self, keysym: u32, down: bool) {
+        if let Some((&id, window)) = self.focus.and_then(|id| self.windows.get_key_value(&id)) {
+            self.ipc_channel.send(window.owner, &key_event(id, keysym, down));
+        }
+    }
+
+    /// Tell the owner of the focused window about the current offer
+    fn notify_selection(&mut self, selection: Selection) {
+        if selection == Selection::Drag {
+            return;
+        }
+        let Some(window) = self.focus.and_then(|id| self.windows.get(&id)) else {
+            return;
+        };
+        let offer = self.selections.current(selection).map_or(0, |offer| offer.id);
+        self.ipc_channel.send(window.owner, &event(EVENT_SELECTION, &[selection.as_u32(), offer]));
+    }
+
+    /// Buttons released: drop the drag on the window under the pointer,
+    /// or cancel it over the background
+    fn end_drag(&mut self, under: Option<(u32, u64, i32, i32)>) {
+        let Some(source) = self.selections.current(Selection::Drag).map(|offer| offer.owner) else {
+            return;
+        };
+        if let Some((window, owner, x, y)) = under {
+            if let Some(offer) = self.selections.drop_on(window, owner) {
+                let drop = event(EVENT_DROP, &[offer.id, window, x as u32, y as u32]);
+                self.ipc_channel.send(owner, &drop);
+            }
+        } else if let Ok(offer) = self.selections.clear(Selection::Drag, source) {
+            self.ipc_channel.send(source, &event(EVENT_CANCELLED, &[Selection::Drag.as_u32(), offer.id]));
+        }
+    }
+
+    fn offer_selection(
+        &mut self,
+        sender: u64,
+        selection: Selection,
+        window: u32,
+        mime_types: Vec<String>,
+    ) -> Result<u32, i32> {
+        match self.windows.get(&window) {
+            Some(existing) if existing.owner == sender => {}
+            _ => return Err(STATUS_ENOENT),
+        }
+        // Only the window the user interacts with may set a selection
+        let holding = selection != Selection::Drag || self.buttons != 0;
+        if self.focus != Some(window) || !holding {
+            return Err(STATUS_EPERM);
+        }
+        let (id, replaced) = self.selections.offer(selection, sender, window, mime_types)?;
+        if let Some(replaced) = replaced {
+            self.ipc_channel.send(replaced.owner, &event(EVENT_CANCELLED, &[selection.as_u32(), replaced.id]));
+        }
+        self.notify_selection(selection);
+        Ok(id)
+    }
+
+    fn clear_selection(&mut self, sender: u64, selection: Selection) -> Result<(), i32> {
+        self.selections.clear(selection, sender)?;
+        self.notify_selection(selection);
+        Ok(())
+    }
+
+    /// Reading a selection is reserved to the window the user is pasting
+    /// into (or dropping on) and to holders of the admin right
+    fn may_paste(&self, sender: u64, selection: Selection, admin: bool) -> bool {
+        if admin {
+            return true;
+        }
+        match selection {
+            Selection::Drag => self.selections.drop_target().map(|(_, owner)| owner) == Some(sender),
+            _ => self.focus.and_then(|id| self.windows.get(&id)).map(|window| window.owner) == Some(sender),
+        }
+    }
+
+    fn receive_selection(
+        &mut self,
+        sender: u64,
+        selection: Selection,
+        offer: u32,
+        mime: u32,
+        buffer: u64,
+    ) -> Result<u32, i32> {
+        let (mapping, size) = map_buffer(buffer, 1)?;
+        let transfer = match self.selections.start_transfer(selection, offer, mime, sender, mapping, size) {
+            Ok(transfer) => transfer,
+            Err(status) => {
+                let _ = orion_sys::shm_detach(mapping);
+                return Err(status);
+            }
+        };
+        let send = event(EVENT_SEND, &[transfer.id, offer, mime, size.min(u32::MAX as usize) as u32]);
+        self.ipc_channel.send(transfer.source, &send);
+        Ok(transfer.id)
+    }
+
+    fn write_transfer(&mut self, sender: u64, id: u32, last: bool, data: &[u8]) -> Result<(), i32> {
+        let transfer = self.selections.write(id, sender, data.len(), last)?;
+        // The chunk fits: write() checked it against the buffer size
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), transfer.mapping as *mut u8, data.len());
+        }
+        let receiver = transfer.receiver;
+        self.ipc_channel.send(receiver, &event(EVENT_DATA, &[id, data.len() as u32, last as u32]));
+        Ok(())
+    }
+
+    fn acknowledge_transfer(&mut self, sender: u64, id: u32) -> Result<(), i32> {
+        let (transfer, finished) = self.selections.acknowledge(id, sender)?;
+        if finished {
+            self.end_transfer(&transfer, &[transfer.source]);
+        } else {
+            self.ipc_channel.send(transfer.source, &event(EVENT_READY, &[id]));
+        }
+        Ok(())
+    }
+
+    fn close_transfer(&mut self, sender: u64, id: u32) -> Result<(), i32> {
+        let transfer = self.selections.close(id, sender)?;
+        if let Some(peer) = transfer.peer(sender) {
+            self.end_transfer(&transfer, &[peer]);
+        }
+        Ok(())
+    }
+
+    fn end_transfer(&mut self, transfer: &selection::Transfer, notify: &[u64]) {
+        let _ = orion_sys::shm_detach(transfer.mapping);
+        for &endpoint in notify {
+            self.ipc_channel.send(endpoint, &event(EVENT_CLOSED, &[transfer.id]));
+        }
+    }
+
+    fn screenshot(&self, target: Target, flags: u32, buffer: u64) -> Result<(u32, u32), i32> {
+        let (source, cursor, origin_x, origin_y) =
+            capture_source(&self.outputs, &self.windows, target).ok_or(STATUS_ENOENT)?;
+        let bounds = source.bounds();
+        let (mapping, size) = map_buffer(buffer, buffer_size(bounds.width, bounds.height))?;
+        let cursor = cursor.filter(|_| flags & CAPTURE_CURSOR != 0).map(|cursor| (cursor, origin_x, origin_y));
+        let (header, pixels) = unsafe { frame_buffer(mapping, size) };
+        let info = FrameInfo { sequence: 0, timestamp: monotonic_ns(), flags };
+        let written = write_frame(header, pixels, &source, &[bounds], cursor, info);
+        let _ = orion_sys::shm_detach(mapping);
+        if written {
+            Ok((bounds.width, bounds.height))
+        } else {
+            Err(STATUS_EINVAL)
+        }
+    }
+
+    fn start_capture(
+        &mut self,
+        sender: u64,
+        target: Target,
+        flags: u32,
+        max_fps: u32,
+        buffer: u64,
+    ) -> Result<u32, i32> {
+        if !self.target_exists(target) {
+            return Err(STATUS_ENOENT);
+        }
+        if self.streams.len() >= MAX_STREAMS {
+            return Err(STATUS_EBUSY);
+        }
+        let (mapping, size) = map_buffer(buffer, FRAME_PIXELS_OFFSET)?;
+        let id = self.allocate_id();
+        self.streams.insert(id, CaptureStream::new(sender, target, flags, max_fps, mapping, size));
+        Ok(id)
+    }
+
+    fn stop_capture(&mut self, sender: u64, id: u32) -> Result<(), i32> {
+        self.stream_of(sender, id)?;
+        let stream = self.streams.remove(&id).unwrap();
+        let _ = orion_sys::shm_detach(stream.mapping);
+        Ok(())
+    }
+
+    fn stream_of(&mut self, sender: u64, id: u32) -> Result<&mut CaptureStream, i32> {
+        match self.streams.get_mut(&id) {
+            Some(stream) if stream.owner == sender => Ok(stream),
+            _ => Err(STATUS_ENOENT),
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match DisplayRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            DisplayRequest::OutputRegister { .. }
+            | DisplayRequest::InputPointer { .. }
+            | DisplayRequest::InputKey { .. } => CAP_ADMIN,
+            DisplayRequest::WindowCreate { .. }
+            | DisplayRequest::SelectionOffer { .. }
+            | DisplayRequest::SelectionClear { .. }
+            | DisplayRequest::TransferWrite { .. }
+            | DisplayRequest::WindowCommit { .. }
+            | DisplayRequest::WindowDestroy { .. }
+            | DisplayRequest::CursorSet { .. }
+            | DisplayRequest::CursorMove { .. } => CAP_WRITE,
+            _ => CAP_READ,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let sender = message.sender;
+        let paste = match request {
+            DisplayRequest::SelectionQuery { selection } | DisplayRequest::SelectionReceive { selection, .. } => {
+                let admin = self.capabilities.check_rights(message.capability, CAP_ADMIN, sender);
+                Some(self.may_paste(sender, selection, admin))
+            }
+            _ => None,
+        };
+        if paste == Some(false) {
+            self.ipc_channel.send(sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let result = match request {
+            DisplayRequest::OutputRegister { framebuffer, width, height, stride } => self
+                .register_output(framebuffer, width, height, stride)
+                .map(|id| payload.extend_from_slice(&id.to_le_bytes())),
+            DisplayRequest::WindowCreate { output, area, buffer } => {
+                self.create_window(sender, output, area, buffer).map(|id| payload.extend_from_slice(&id.to_le_bytes()))
+            }
+            DisplayRequest::WindowCommit { window, rects } => self.commit_window(sender, window, rects),
+            DisplayRequest::WindowDestroy { window } => self.destroy_window(sender, window),
+            DisplayRequest::CursorSet { output, hot_x, hot_y, width, height, image } => {
+                self.set_cursor(output, hot_x, hot_y, width, height, image)
+            }
+            DisplayRequest::CursorMove { output, x, y } => self.move_cursor(output, x, y),
+            DisplayRequest::Screenshot { target, flags, buffer } => {
+                self.screenshot(target, flags, buffer).map(|(width, height)| {
+                    payload.extend_from_slice(&width.to_le_bytes());
+                    payload.extend_from_slice(&height.to_le_bytes());
+                })
+            }
+            DisplayRequest::CaptureStart { target, flags, max_fps, buffer } => self
+                .start_capture(sender, target, flags, max_fps, buffer)
+                .map(|id| payload.extend_from_slice(&id.to_le_bytes())),
+            DisplayRequest::CaptureRelease { stream } => self.stream_of(sender, stream).map(|stream| stream.release()),
+            DisplayRequest::CaptureStop { stream } => self.stop_capture(sender, stream),
+            DisplayRequest::ListOutputs => {
+                for (&id, output) in self.outputs.iter() {
+                    for value in [id, output.width, output.height] {
+                        payload.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                Ok(())
+            }
+            DisplayRequest::ListWindows => {
+                for (&id, window) in self.windows.iter() {
+                    for value in [
+                        id,
+                        window.output,
+                        window.area.x as u32,
+                        window.area.y as u32,
+                        window.area.width,
+                        window.area.height,
+                    ] {
+                        payload.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                Ok(())
+            }
+            DisplayRequest::InputPointer { output, x, y, buttons } => self.pointer_input(output, x, y, buttons),
+            DisplayRequest::InputKey { keysym, down } => {
+                self.key_input(keysym, down);
+                Ok(())
+            }
+            DisplayRequest::SelectionOffer { selection, window, mime_types } => self
+                .offer_selection(sender, selection, window, mime_types)
+                .map(|id| payload.extend_from_slice(&id.to_le_bytes())),
+            DisplayRequest::SelectionClear { selection } => self.clear_selection(sender, selection),
+            DisplayRequest::SelectionQuery { selection } => match self.selections.current(selection) {
+                Some(offer) => {
+                    payload.extend_from_slice(&offer.id.to_le_bytes());
+                    payload.extend_from_slice(&(offer.mime_types.len() as u32).to_le_bytes());
+                    write_strings(&mut payload, &offer.mime_types);
+                    Ok(())
+                }
+                None => Err(STATUS_ENOENT),
+            },
+            DisplayRequest::SelectionReceive { selection, offer, mime, buffer } => self
+                .receive_selection(sender, selection, offer, mime, buffer)
+                .map(|id| payload.extend_from_slice(&id.to_le_bytes())),
+            DisplayRequest::TransferWrite { transfer, last, data } => {
+                self.write_transfer(sender, transfer, last, &data)
+            }
+            DisplayRequest::TransferAck { transfer } => self.acknowledge_transfer(sender, transfer),
+            DisplayRequest::TransferClose { transfer } => self.close_transfer(sender, transfer),
+        };
+
+        let status = match result {
+            Ok(()) => STATUS_OK,
+            Err(status) => status,
+        };
+        self.ipc_channel.send(sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    let mut server = DisplayServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
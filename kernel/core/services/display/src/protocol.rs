@@ -0,0 +1,472 @@
+/*
+ * Orion Operating System - Display Server Protocol
+ *
+ * IPC requests of the display server. All fields are little-endian; every
+ * message starts with a 32-bit opcode and every reply starts with a 32-bit
+ * signed status (0 or a negative errno). Buffers are shared memory objects
+ * whose capability travels in the payload; the message capability is the
+ * one checked for rights.
+ *
+ *   OUTPUT_REGISTER  framebuffer:u64 width:u32 height:u32
+ *                    stride:u32                    -> output:u32
+ *   WINDOW_CREATE    output:u32 x:i32 y:i32 width:u32 height:u32
+ *                    buffer:u64                    -> window:u32
+ *   WINDOW_COMMIT    window:u32 count:u32 rects    -> (empty)
+ *   WINDOW_DESTROY   window:u32                    -> (empty)
+ *   CURSOR_SET       output:u32 hot_x:u32 hot_y:u32 width:u32
+ *                    height:u32 pixels:u32...      -> (empty)
+ *   CURSOR_MOVE      output:u32 x:i32 y:i32        -> (empty)
+ *   SCREENSHOT       target flags:u32 buffer:u64   -> width:u32 height:u32
+ *   CAPTURE_START    target flags:u32 max_fps:u32
+ *                    buffer:u64                    -> stream:u32
+ *   CAPTURE_RELEASE  stream:u32                    -> (empty)
+ *   CAPTURE_STOP     stream:u32                    -> (empty)
+ *   LIST_OUTPUTS     (none)                        -> records: output:u32
+ *                                                     width:u32 height:u32
+ *   LIST_WINDOWS     (none)                        -> records: window:u32
+ *                                                     output:u32 x:i32 y:i32
+ *                                                     width:u32 height:u32
+ *   INPUT_POINTER    output:u32 x:i32 y:i32
+ *                    buttons:u32                   -> (empty)
+ *   INPUT_KEY        keysym:u32 down:u32           -> (empty)
+ *   SELECTION_OFFER  selection:u32 window:u32
+ *                    count:u32 mime types          -> offer:u32
+ *   SELECTION_CLEAR  selection:u32                 -> (empty)
+ *   SELECTION_QUERY  selection:u32                 -> offer:u32 count:u32
+ *                                                     mime types
+ *   SELECTION_RECEIVE selection:u32 offer:u32
+ *                    mime:u32 buffer:u64           -> transfer:u32
+ *   TRANSFER_WRITE   transfer:u32 last:u32
+ *                    length:u32 data               -> (empty)
+ *   TRANSFER_ACK     transfer:u32                  -> (empty)
+ *   TRANSFER_CLOSE   transfer:u32                  -> (empty)
+ *
+ * A target is kind:u32 (1 output, 2 window) followed by id:u32; rects are
+ * x:i32 y:i32 width:u32 height:u32 in window coordinates, none meaning the
+ * whole window. A cursor of width 0 hides the pointer. The capture buffer
+ * layout is described in capture.rs. Each frame of a stream is announced
+ * to its owner with a FRAME event (stream:u32 sequence:u64); the next one
+ * is only written after CAPTURE_RELEASE. ENDED (stream:u32) tells the
+ * owner its target went away and the stream was stopped.
+ *
+ * Input sources (HID drivers, remote desktop servers) report pointer
+ * positions in output coordinates with a button mask (bit 0 left, 1
+ * middle, 2 right) and keys as X11 keysyms. The window under the pointer
+ * receives POINTER (window:u32 x:i32 y:i32 buttons:u32) in window
+ * coordinates; pressing a button over a window gives it the keyboard
+ * focus, and the focused window receives KEY (window:u32 keysym:u32
+ * down:u32).
+ *
+ * Selections are 1 clipboard, 2 primary and 3 drag (see selection.rs); a
+ * MIME type is length:u32 followed by that many bytes, and mime in
+ * SELECTION_RECEIVE indexes the offer's list. Offering needs the write
+ * right and, for the clipboard and primary selection, a window of the
+ * sender with the keyboard focus; a drag is offered while a button is
+ * held over the sender's window. Querying and receiving needs the read
+ * right and the focused window (the window dropped on, for a drag), or
+ * the admin right. The focused window's owner gets SELECTION
+ * (selection:u32 offer:u32, 0 once withdrawn) when an offer changes, and
+ * a replaced source gets CANCELLED (selection:u32 offer:u32). Dropping
+ * a drag sends DROP (offer:u32 window:u32 x:i32 y:i32) to the window
+ * under the pointer.
+ *
+ * Receiving asks the source to send with SEND (transfer:u32 offer:u32
+ * mime:u32 max_chunk:u32). Each TRANSFER_WRITE chunk is copied to the
+ * start of the receiver's buffer and announced with DATA (transfer:u32
+ * length:u32 last:u32); the next chunk is refused with EAGAIN until the
+ * receiver's TRANSFER_ACK, which the source sees as READY (transfer:u32).
+ * CLOSED (transfer:u32) tells a party that the other one, or the server,
+ * ended the transfer.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::capture::Target;
+use crate::compose::MAX_CURSOR_SIZE;
+use crate::damage::Rect;
+use crate::selection::{Selection, MAX_MIME_LENGTH, MAX_MIME_TYPES};
+
+// Opcodes
+pub const OP_OUTPUT_REGISTER: u32 = 1;
+pub const OP_WINDOW_CREATE: u32 = 2;
+pub const OP_WINDOW_COMMIT: u32 = 3;
+pub const OP_WINDOW_DESTROY: u32 = 4;
+pub const OP_CURSOR_SET: u32 = 5;
+pub const OP_CURSOR_MOVE: u32 = 6;
+pub const OP_SCREENSHOT: u32 = 7;
+pub const OP_CAPTURE_START: u32 = 8;
+pub const OP_CAPTURE_RELEASE: u32 = 9;
+pub const OP_CAPTURE_STOP: u32 = 10;
+pub const OP_LIST_OUTPUTS: u32 = 11;
+pub const OP_LIST_WINDOWS: u32 = 12;
+pub const OP_INPUT_POINTER: u32 = 13;
+pub const OP_INPUT_KEY: u32 = 14;
+pub const OP_SELECTION_OFFER: u32 = 15;
+pub const OP_SELECTION_CLEAR: u32 = 16;
+pub const OP_SELECTION_QUERY: u32 = 17;
+pub const OP_SELECTION_RECEIVE: u32 = 18;
+pub const OP_TRANSFER_WRITE: u32 = 19;
+pub const OP_TRANSFER_ACK: u32 = 20;
+pub const OP_TRANSFER_CLOSE: u32 = 21;
+
+// Events sent to clients
+pub const EVENT_CAPTURE_FRAME: u32 = 0x8001;
+pub const EVENT_CAPTURE_ENDED: u32 = 0x8002;
+pub const EVENT_POINTER: u32 = 0x8003;
+pub const EVENT_KEY: u32 = 0x8004;
+pub const EVENT_SELECTION: u32 = 0x8005;
+pub const EVENT_CANCELLED: u32 = 0x8006;
+pub const EVENT_SEND: u32 = 0x8007;
+pub const EVENT_DATA: u32 = 0x8008;
+pub const EVENT_READY: u32 = 0x8009;
+pub const EVENT_CLOSED: u32 = 0x800a;
+pub const EVENT_DROP: u32 = 0x800b;
+
+// Target kinds
+pub const TARGET_OUTPUT: u32 = 1;
+pub const TARGET_WINDOW: u32 = 2;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+/// Rectangles in one WINDOW_COMMIT
+pub const MAX_COMMIT_RECTS: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DisplayRequest {
+    OutputRegister { framebuffer: u64, width: u32, height: u32, stride: u32 },
+    WindowCreate { output: u32, area: Rect, buffer: u64 },
+    WindowCommit { window: u32, rects: Vec<Rect> },
+    WindowDestroy { window: u32 },
+    CursorSet { output: u32, hot_x: u32, hot_y: u32, width: u32, height: u32, image: Vec<u32> },
+    CursorMove { output: u32, x: i32, y: i32 },
+    Screenshot { target: Target, flags: u32, buffer: u64 },
+    CaptureStart { target: Target, flags: u32, max_fps: u32, buffer: u64 },
+    CaptureRelease { stream: u32 },
+    CaptureStop { stream: u32 },
+    ListOutputs,
+    ListWindows,
+    InputPointer { output: u32, x: i32, y: i32, buttons: u32 },
+    InputKey { keysym: u32, down: bool },
+    SelectionOffer { selection: Selection, window: u32, mime_types: Vec<String> },
+    SelectionClear { selection: Selection },
+    SelectionQuery { selection: Selection },
+    SelectionReceive { selection: Selection, offer: u32, mime: u32, buffer: u64 },
+    TransferWrite { transfer: u32, last: bool, data: Vec<u8> },
+    TransferAck { transfer: u32 },
+    TransferClose { transfer: u32 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    read_u32(data, offset).map(|value| value as i32)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+fn read_rect(data: &[u8], offset: usize) -> Option<Rect> {
+    Some(Rect::new(
+        read_i32(data, offset)?,
+        read_i32(data, offset + 4)?,
+        read_u32(data, offset + 8)?,
+        read_u32(data, offset + 12)?,
+    ))
+}
+
+fn read_target(data: &[u8], offset: usize) -> Option<Target> {
+    let id = read_u32(data, offset + 4)?;
+    match read_u32(data, offset)? {
+        TARGET_OUTPUT => Some(Target::Output(id)),
+        TARGET_WINDOW => Some(Target::Window(id)),
+        _ => None,
+    }
+}
+
+fn read_selection(data: &[u8], offset: usize) -> Option<Selection> {
+    Selection::from_u32(read_u32(data, offset)?)
+}
+
+/// `count` length-prefixed strings starting at `offset`
+fn read_strings(data: &[u8], mut offset: usize, count: usize) -> Option<Vec<String>> {
+    if count > MAX_MIME_TYPES {
+        return None;
+    }
+    let mut strings = Vec::with_capacity(count);
+    for _ in 0..count {
+        let length = read_u32(data, offset)? as usize;
+        if length > MAX_MIME_LENGTH {
+            return None;
+        }
+        let bytes = data.get(offset + 4..offset + 4 + length)?;
+        strings.push(String::from(core::str::from_utf8(bytes).ok()?));
+        offset += 4 + length;
+    }
+    Some(strings)
+}
+
+/// Append length-prefixed strings to a payload
+pub fn write_strings(out: &mut Vec<u8>, strings: &[String]) {
+    for string in strings {
+        out.extend_from_slice(&(string.len() as u32).to_le_bytes());
+        out.extend_from_slice(string.as_bytes());
+    }
+}
+
+impl DisplayRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_OUTPUT_REGISTER => Some(DisplayRequest::OutputRegister {
+                framebuffer: read_u64(data, 4)?,
+                width: read_u32(data, 12)?,
+                height: read_u32(data, 16)?,
+                stride: read_u32(data, 20)?,
+            }),
+            OP_WINDOW_CREATE => Some(DisplayRequest::WindowCreate {
+                output: read_u32(data, 4)?,
+                area: read_rect(data, 8)?,
+                buffer: read_u64(data, 24)?,
+            }),
+            OP_WINDOW_COMMIT => {
+                let count = read_u32(data, 8)? as usize;
+                if count > MAX_COMMIT_RECTS {
+                    return None;
+                }
+                let rects = (0..count).map(|index| read_rect(data, 12 + 16 * index)).collect::<Option<Vec<Rect>>>()?;
+                Some(DisplayRequest::WindowCommit { window: read_u32(data, 4)?, rects })
+            }
+            OP_WINDOW_DESTROY => Some(DisplayRequest::WindowDestroy { window: read_u32(data, 4)? }),
+            OP_CURSOR_SET => {
+                let (width, height) = (read_u32(data, 16)?, read_u32(data, 20)?);
+                if width > MAX_CURSOR_SIZE || height > MAX_CURSOR_SIZE {
+                    return None;
+                }
+                let pixels = (width * height) as usize;
+                let image = (0..pixels).map(|index| read_u32(data, 24 + 4 * index)).collect::<Option<Vec<u32>>>()?;
+                Some(DisplayRequest::CursorSet {
+                    output: read_u32(data, 4)?,
+                    hot_x: read_u32(data, 8)?,
+                    hot_y: read_u32(data, 12)?,
+                    width,
+                    height,
+                    image,
+                })
+            }
+            OP_CURSOR_MOVE => Some(DisplayRequest::CursorMove {
+                output: read_u32(data, 4)?,
+                x: read_i32(data, 8)?,
+                y: read_i32(data, 12)?,
+            }),
+            OP_SCREENSHOT => Some(DisplayRequest::Screenshot {
+                target: read_target(data, 4)?,
+                flags: read_u32(data, 12)?,
+                buffer: read_u64(data, 16)?,
+            }),
+            OP_CAPTURE_START => Some(DisplayRequest::CaptureStart {
+                target: read_target(data, 4)?,
+                flags: read_u32(data, 12)?,
+                max_fps: read_u32(data, 16)?,
+                buffer: read_u64(data, 20)?,
+            }),
+            OP_CAPTURE_RELEASE => Some(DisplayRequest::CaptureRelease { stream: read_u32(data, 4)? }),
+            OP_CAPTURE_STOP => Some(DisplayRequest::CaptureStop { stream: read_u32(data, 4)? }),
+            OP_LIST_OUTPUTS => Some(DisplayRequest::ListOutputs),
+            OP_LIST_WINDOWS => Some(DisplayRequest::ListWindows),
+            OP_INPUT_POINTER => Some(DisplayRequest::InputPointer {
+                output: read_u32(data, 4)?,
+                x: read_i32(data, 8)?,
+                y: read_i32(data, 12)?,
+                buttons: read_u32(data, 16)?,
+            }),
+            OP_INPUT_KEY => {
+                Some(DisplayRequest::InputKey { keysym: read_u32(data, 4)?, down: read_u32(data, 8)? != 0 })
+            }
+            OP_SELECTION_OFFER => Some(DisplayRequest::SelectionOffer {
+                selection: read_selection(data, 4)?,
+                window: read_u32(data, 8)?,
+                mime_types: read_strings(data, 16, read_u32(data, 12)? as usize)?,
+            }),
+            OP_SELECTION_CLEAR => Some(DisplayRequest::SelectionClear { selection: read_selection(data, 4)? }),
+            OP_SELECTION_QUERY => Some(DisplayRequest::SelectionQuery { selection: read_selection(data, 4)? }),
+            OP_SELECTION_RECEIVE => Some(DisplayRequest::SelectionReceive {
+                selection: read_selection(data, 4)?,
+                offer: read_u32(data, 8)?,
+                mime: read_u32(data, 12)?,
+                buffer: read_u64(data, 16)?,
+            }),
+            OP_TRANSFER_WRITE => {
+                let length = read_u32(data, 12)? as usize;
+                Some(DisplayRequest::TransferWrite {
+                    transfer: read_u32(data, 4)?,
+                    last: read_u32(data, 8)? != 0,
+                    data: data.get(16..16usize.checked_add(length)?)?.to_vec(),
+                })
+            }
+            OP_TRANSFER_ACK => Some(DisplayRequest::TransferAck { transfer: read_u32(data, 4)? }),
+            OP_TRANSFER_CLOSE => Some(DisplayRequest::TransferClose { transfer: read_u32(data, 4)? }),
+            _ => None,
+        }
+    }
+}
+
+/// FRAME event for a capture client
+pub fn frame_event(stream: u32, sequence: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16);
+    out.extend_from_slice(&EVENT_CAPTURE_FRAME.to_le_bytes());
+    out.extend_from_slice(&stream.to_le_bytes());
+    out.extend_from_slice(&sequence.to_le_bytes());
+    out
+}
+
+/// ENDED event for a capture client
+pub fn ended_event(stream: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8);
+    out.extend_from_slice(&EVENT_CAPTURE_ENDED.to_le_bytes());
+    out.extend_from_slice(&stream.to_le_bytes());
+    out
+}
+
+/// POINTER event for the window under the pointer
+pub fn pointer_event(window: u32, x: i32, y: i32, buttons: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(20);
+    out.extend_from_slice(&EVENT_POINTER.to_le_bytes());
+    out.extend_from_slice(&window.to_le_bytes());
+    out.extend_from_slice(&x.to_le_bytes());
+    out.extend_from_slice(&y.to_le_bytes());
+    out.extend_from_slice(&buttons.to_le_bytes());
+    out
+}
+
+/// KEY event for the focused window
+pub fn key_event(window: u32, keysym: u32, down: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16);
+    out.extend_from_slice(&EVENT_KEY.to_le_bytes());
+    out.extend_from_slice(&window.to_le_bytes());
+    out.extend_from_slice(&keysym.to_le_bytes());
+    out.extend_from_slice(&(down as u32).to_le_bytes());
+    out
+}
+
+/// Event made of an opcode and 32-bit fields
+pub fn event(opcode: u32, fields: &[u32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + 4 * fields.len());
+    out.extend_from_slice(&opcode.to_le_bytes());
+    for field in fields {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out
+}
+
+// Keymap server requests (see services/keymap/src/protocol.rs)
+const KEYMAP_OP_FOCUS: u32 = 2;
+const KEYMAP_OP_FORGET: u32 = 3;
+const KEYMAP_TARGET_WINDOW: u32 = 2;
+
+/// FOCUS request telling the keymap server which window gets the keys
+pub fn keymap_focus(window: u32, owner: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(20);
+    for value in [KEYMAP_OP_FOCUS, KEYMAP_TARGET_WINDOW, window] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&owner.to_le_bytes());
+    out
+}
+
+/// FORGET request dropping the layout of a destroyed window
+pub fn keymap_forget(window: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(12);
+    for value in [KEYMAP_OP_FORGET, KEYMAP_TARGET_WINDOW, window] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut message = Vec::new();
+        message.extend_from_slice(&OP_CAPTURE_START.to_le_bytes());
+        message.extend_from_slice(&TARGET_WINDOW.to_le_bytes());
+        message.extend_from_slice(&3u32.to_le_bytes());
+        message.extend_from_slice(&1u32.to_le_bytes());
+        message.extend_from_slice(&30u32.to_le_bytes());
+        message.extend_from_slice(&0x44u64.to_le_bytes());
+        assert_eq!(
+            DisplayRequest::decode(&message),
+            Some(DisplayRequest::CaptureStart { target: Target::Window(3), flags: 1, max_fps: 30, buffer: 0x44 })
+        );
+        assert_eq!(DisplayRequest::decode(&message[..24]), None);
+        message[4..8].copy_from_slice(&9u32.to_le_bytes());
+        assert_eq!(DisplayRequest::decode(&message), None);
+
+        let mut commit = Vec::new();
+        commit.extend_from_slice(&OP_WINDOW_COMMIT.to_le_bytes());
+        commit.extend_from_slice(&5u32.to_le_bytes());
+        commit.extend_from_slice(&1u32.to_le_bytes());
+        for value in [-2i32, 4, 10, 20] {
+            commit.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(
+            DisplayRequest::decode(&commit),
+            Some(DisplayRequest::WindowCommit { window: 5, rects: alloc::vec![Rect::new(-2, 4, 10, 20)] })
+        );
+
+        // Cursor larger than MAX_CURSOR_SIZE
+        let mut cursor = Vec::new();
+        for value in [OP_CURSOR_SET, 1, 0, 0, MAX_CURSOR_SIZE + 1, 1] {
+            cursor.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(DisplayRequest::decode(&cursor), None);
+
+        let mut offer = Vec::new();
+        for value in [OP_SELECTION_OFFER, Selection::Primary.as_u32(), 4, 1] {
+            offer.extend_from_slice(&value.to_le_bytes());
+        }
+        write_strings(&mut offer, &[String::from("text/plain")]);
+        assert_eq!(
+            DisplayRequest::decode(&offer),
+            Some(DisplayRequest::SelectionOffer {
+                selection: Selection::Primary,
+                window: 4,
+                mime_types: alloc::vec![String::from("text/plain")],
+            })
+        );
+        assert_eq!(DisplayRequest::decode(&offer[..offer.len() - 1]), None);
+
+        let mut write = Vec::new();
+        for value in [OP_TRANSFER_WRITE, 2, 1, 3] {
+            write.extend_from_slice(&value.to_le_bytes());
+        }
+        write.extend_from_slice(b"abc");
+        assert_eq!(
+            DisplayRequest::decode(&write),
+            Some(DisplayRequest::TransferWrite { transfer: 2, last: true, data: b"abc".to_vec() })
+        );
+    }
+}
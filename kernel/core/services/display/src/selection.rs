@@ -0,0 +1,332 @@
+/*
+ * Orion Operating System - Selections and Data Transfer
+ *
+ * Clipboard, primary selection and drag-and-drop. A source window offers
+ * data for a selection under a list of MIME types; the server keeps only
+ * the current offer of each selection and tells the previous source when
+ * it is replaced. Nothing is copied until a client receives the offer in
+ * one of its types: the server then asks the source to send it and relays
+ * the data into a shared memory buffer of the receiver, one chunk at a
+ * time. Each chunk fills the buffer from its start and must be
+ * acknowledged before the next one is accepted, so content of any size
+ * goes through a fixed buffer.
+ *
+ * The clipboard is set explicitly (copy) and the primary selection
+ * follows the last text selected; both are offered by the focused window.
+ * A drag offer is made while a button is held over the source window and
+ * becomes receivable by the window it is dropped on.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::protocol::{STATUS_EAGAIN, STATUS_EBUSY, STATUS_EINVAL, STATUS_ENOENT, STATUS_EPERM};
+
+// Selection identifiers
+pub const SELECTION_CLIPBOARD: u32 = 1;
+pub const SELECTION_PRIMARY: u32 = 2;
+pub const SELECTION_DRAG: u32 = 3;
+
+/// MIME types in one offer
+pub const MAX_MIME_TYPES: usize = 16;
+pub const MAX_MIME_LENGTH: usize = 64;
+
+/// Transfers in progress across all clients
+pub const MAX_TRANSFERS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+    Drag,
+}
+
+impl Selection {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            SELECTION_CLIPBOARD => Some(Selection::Clipboard),
+            SELECTION_PRIMARY => Some(Selection::Primary),
+            SELECTION_DRAG => Some(Selection::Drag),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Selection::Clipboard => SELECTION_CLIPBOARD,
+            Selection::Primary => SELECTION_PRIMARY,
+            Selection::Drag => SELECTION_DRAG,
+        }
+    }
+}
+
+/// MIME type as sent by clients: "type/subtype" with optional parameters
+pub fn valid_mime_type(mime: &str) -> bool {
+    !mime.is_empty()
+        && mime.len() <= MAX_MIME_LENGTH
+        && mime.contains('/')
+        && mime.bytes().all(|byte| byte.is_ascii_graphic() || byte == b' ')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub id: u32,
+    pub selection: Selection,
+    pub owner: u64,
+    pub window: u32,
+    pub mime_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub id: u32,
+    pub offer: u32,
+    pub source: u64,
+    pub receiver: u64,
+    /// Receiver buffer mapping
+    pub mapping: u64,
+    pub size: usize,
+    /// A chunk waits in the buffer for the receiver
+    pending: bool,
+    finished: bool,
+}
+
+impl Transfer {
+    /// Peer of `endpoint` in the transfer, if it takes part in it
+    pub fn peer(&self, endpoint: u64) -> Option<u64> {
+        if endpoint == self.source {
+            Some(self.receiver)
+        } else if endpoint == self.receiver {
+            Some(self.source)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Selections {
+    offers: BTreeMap<Selection, Offer>,
+    /// Window a drag was dropped on and its owner
+    drop_target: Option<(u32, u64)>,
+    transfers: BTreeMap<u32, Transfer>,
+    next_id: u32,
+}
+
+impl Selections {
+    pub fn new() -> Self {
+        Self { offers: BTreeMap::new(), drop_target: None, transfers: BTreeMap::new(), next_id: 1 }
+    }
+
+    fn allocate_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        id
+    }
+
+    pub fn current(&self, selection: Selection) -> Option<&Offer> {
+        self.offers.get(&selection)
+    }
+
+    pub fn drop_target(&self) -> Option<(u32, u64)> {
+        self.drop_target
+    }
+
+    /// Make a new offer current, returning it with the one it replaces
+    pub fn offer(
+        &mut self,
+        selection: Selection,
+        owner: u64,
+        window: u32,
+        mime_types: Vec<String>,
+    ) -> Result<(u32, Option<Offer>), i32> {
+        if mime_types.is_empty()
+            || mime_types.len() > MAX_MIME_TYPES
+            || !mime_types.iter().all(|mime| valid_mime_type(mime))
+        {
+            return Err(STATUS_EINVAL);
+        }
+        let id = self.allocate_id();
+        if selection == Selection::Drag {
+            self.drop_target = None;
+        }
+        let replaced = self.offers.insert(selection, Offer { id, selection, owner, window, mime_types });
+        Ok((id, replaced))
+    }
+
+    /// Withdraw the current offer of `selection` if `owner` made it
+    pub fn clear(&mut self, selection: Selection, owner: u64) -> Result<Offer, i32> {
+        match self.offers.get(&selection) {
+            Some(offer) if offer.owner == owner => {}
+            Some(_) => return Err(STATUS_EPERM),
+            None => return Err(STATUS_ENOENT),
+        }
+        if selection == Selection::Drag {
+            self.drop_target = None;
+        }
+        Ok(self.offers.remove(&selection).unwrap())
+    }
+
+    /// The drag ended over `window`, whose owner may now receive it
+    pub fn drop_on(&mut self, window: u32, owner: u64) -> Option<&Offer> {
+        let offer = self.offers.get(&Selection::Drag)?;
+        self.drop_target = Some((window, owner));
+        Some(offer)
+    }
+
+    /// Start relaying offer `offer_id` of `selection` in its MIME type
+    /// `mime` into the receiver's buffer
+    pub fn start_transfer(
+        &mut self,
+        selection: Selection,
+        offer_id: u32,
+        mime: u32,
+        receiver: u64,
+        mapping: u64,
+        size: usize,
+    ) -> Result<Transfer, i32> {
+        let offer = match self.offers.get(&selection) {
+            Some(offer) if offer.id == offer_id => offer,
+            _ => return Err(STATUS_ENOENT),
+        };
+        if mime as usize >= offer.mime_types.len() || size == 0 {
+            return Err(STATUS_EINVAL);
+        }
+        if self.transfers.len() >= MAX_TRANSFERS {
+            return Err(STATUS_EBUSY);
+        }
+        let source = offer.owner;
+        let id = self.allocate_id();
+        let transfer =
+            Transfer { id, offer: offer_id, source, receiver, mapping, size, pending: false, finished: false };
+        self.transfers.insert(id, transfer.clone());
+        Ok(transfer)
+    }
+
+    /// Accept a chunk of `length` bytes from the source; the caller copies
+    /// it into the buffer
+    pub fn write(&mut self, id: u32, source: u64, length: usize, last: bool) -> Result<&Transfer, i32> {
+        let transfer = match self.transfers.get_mut(&id) {
+            Some(transfer) if transfer.source == source => transfer,
+            _ => return Err(STATUS_ENOENT),
+        };
+        if transfer.finished || length > transfer.size {
+            return Err(STATUS_EINVAL);
+        }
+        if transfer.pending {
+            return Err(STATUS_EAGAIN);
+        }
+        transfer.pending = true;
+        transfer.finished = last;
+        Ok(transfer)
+    }
+
+    /// The receiver consumed the chunk; the transfer is returned once the
+    /// last one is acknowledged, and removed
+    pub fn acknowledge(&mut self, id: u32, receiver: u64) -> Result<(Transfer, bool), i32> {
+        let transfer = match self.transfers.get_mut(&id) {
+            Some(transfer) if transfer.receiver == receiver && transfer.pending => transfer,
+            Some(transfer) if transfer.receiver == receiver => return Err(STATUS_EINVAL),
+            _ => return Err(STATUS_ENOENT),
+        };
+        transfer.pending = false;
+        if transfer.finished {
+            return Ok((self.transfers.remove(&id).unwrap(), true));
+        }
+        Ok((transfer.clone(), false))
+    }
+
+    /// Abort a transfer on behalf of either side
+    pub fn close(&mut self, id: u32, endpoint: u64) -> Result<Transfer, i32> {
+        match self.transfers.get(&id) {
+            Some(transfer) if transfer.peer(endpoint).is_some() => Ok(self.transfers.remove(&id).unwrap()),
+            _ => Err(STATUS_ENOENT),
+        }
+    }
+
+    /// Drop the offers of a destroyed window and the transfers reading
+    /// from them or waiting on the window as drop target
+    pub fn window_destroyed(&mut self, window: u32) -> (Vec<Offer>, Vec<Transfer>) {
+        if self.drop_target.map(|(target, _)| target) == Some(window) {
+            self.drop_target = None;
+        }
+        let selections: Vec<Selection> =
+            self.offers.iter().filter(|(_, offer)| offer.window == window).map(|(&selection, _)| selection).collect();
+        let offers: Vec<Offer> = selections.iter().filter_map(|selection| self.offers.remove(selection)).collect();
+        let ids: Vec<u32> = self
+            .transfers
+            .iter()
+            .filter(|(_, transfer)| offers.iter().any(|offer| offer.id == transfer.offer))
+            .map(|(&id, _)| id)
+            .collect();
+        let transfers = ids.iter().filter_map(|id| self.transfers.remove(id)).collect();
+        (offers, transfers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn text() -> Vec<String> {
+        vec!["text/plain;charset=utf-8".to_string(), "text/html".to_string()]
+    }
+
+    #[test]
+    fn test_replaces_offers() {
+        let mut selections = Selections::new();
+        let (first, replaced) = selections.offer(Selection::Clipboard, 10, 1, text()).unwrap();
+        assert_eq!(replaced, None);
+        let (second, replaced) = selections.offer(Selection::Clipboard, 11, 2, text()).unwrap();
+        assert_eq!(replaced.map(|offer| offer.id), Some(first));
+        assert_eq!(selections.current(Selection::Clipboard).map(|offer| offer.id), Some(second));
+        assert!(selections.current(Selection::Primary).is_none());
+
+        assert_eq!(selections.offer(Selection::Primary, 10, 1, vec!["plain".to_string()]), Err(STATUS_EINVAL));
+        assert_eq!(selections.clear(Selection::Clipboard, 10), Err(STATUS_EPERM));
+        assert!(selections.clear(Selection::Clipboard, 11).is_ok());
+
+        // Destroying the source window withdraws its drag offer
+        let (drag, _) = selections.offer(Selection::Drag, 12, 3, text()).unwrap();
+        assert_eq!(selections.drop_on(4, 13).map(|offer| offer.id), Some(drag));
+        assert_eq!(selections.drop_target(), Some((4, 13)));
+        let (offers, _) = selections.window_destroyed(3);
+        assert_eq!(offers.len(), 1);
+        assert!(selections.current(Selection::Drag).is_none());
+    }
+
+    #[test]
+    fn test_relays_chunks() {
+        let mut selections = Selections::new();
+        let (offer, _) = selections.offer(Selection::Clipboard, 10, 1, text()).unwrap();
+        assert_eq!(selections.start_transfer(Selection::Clipboard, offer, 2, 20, 0, 64), Err(STATUS_EINVAL));
+        assert_eq!(selections.start_transfer(Selection::Clipboard, offer + 1, 0, 20, 0, 64), Err(STATUS_ENOENT));
+        let transfer = selections.start_transfer(Selection::Clipboard, offer, 1, 20, 0, 64).unwrap();
+        assert_eq!(transfer.source, 10);
+
+        // One chunk at a time, no larger than the buffer, only from the source
+        assert_eq!(selections.write(transfer.id, 20, 8, false).err(), Some(STATUS_ENOENT));
+        assert_eq!(selections.write(transfer.id, 10, 65, false).err(), Some(STATUS_EINVAL));
+        assert!(selections.write(transfer.id, 10, 64, false).is_ok());
+        assert_eq!(selections.write(transfer.id, 10, 8, true).err(), Some(STATUS_EAGAIN));
+        assert_eq!(selections.acknowledge(transfer.id, 20).map(|(_, done)| done), Ok(false));
+        assert_eq!(selections.acknowledge(transfer.id, 20).err(), Some(STATUS_EINVAL));
+        assert!(selections.write(transfer.id, 10, 8, true).is_ok());
+        assert_eq!(selections.acknowledge(transfer.id, 20).map(|(_, done)| done), Ok(true));
+        assert_eq!(selections.close(transfer.id, 10).err(), Some(STATUS_ENOENT));
+
+        // Replacing the offer leaves running transfers alone; closing ends them
+        let transfer = selections.start_transfer(Selection::Clipboard, offer, 0, 20, 0, 64).unwrap();
+        selections.offer(Selection::Clipboard, 11, 2, text()).unwrap();
+        assert_eq!(selections.close(transfer.id, 30).err(), Some(STATUS_ENOENT));
+        assert_eq!(selections.close(transfer.id, 20).map(|closed| closed.source), Ok(10));
+    }
+}
@@ -0,0 +1,136 @@
+/*
+ * Orion Operating System - Distributed Lock Manager Server
+ *
+ * Serves the lock table of orion_dlm over IPC to the storage services
+ * sharing a pool: replication, clustered file systems and volume
+ * management take shared or exclusive locks on named resources and renew
+ * their node's lease while they hold them. A LOCK that cannot be granted
+ * right away is answered once it is granted, times out or fails, so the
+ * caller simply blocks on its reply.
+ *
+ * Node failures reach the server two ways: the cluster membership layer
+ * sends NODE_DOWN as soon as it declares a node dead, and a node that
+ * stops renewing loses its lease on the next poll. Either way its locks
+ * are released to the next waiters, with higher fencing tokens.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use orion_cap::Capability;
+use orion_dlm::protocol::{STATUS_EINVAL, STATUS_EPERM};
+use orion_dlm::server::{handle, tick, Outbox};
+use orion_dlm::{DlmRequest, LockManager};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+use orion_sys::clock_get;
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 10_000_000;
+
+/// Lease granted to each node by RENEW
+const LEASE_NS: u64 = orion_dlm::lock::DEFAULT_LEASE_NS;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+/// Only the kernel may report process exits
+const KERNEL_ENDPOINT: u64 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+struct DlmServer {
+    manager: LockManager,
+    outbox: Outbox,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl DlmServer {
+    fn new() -> Self {
+        Self {
+            manager: LockManager::new(LEASE_NS),
+            outbox: Outbox::new(),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+
+            tick(&mut self.manager, monotonic_ns(), &mut self.outbox);
+            self.flush();
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn flush(&mut self) {
+        for (recipient, message) in self.outbox.drain(..) {
+            self.ipc_channel.send(recipient, &message);
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match DlmRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            DlmRequest::ProcessExit { .. } => {
+                if message.sender == KERNEL_ENDPOINT {
+                    handle(&mut self.manager, message.sender, request, monotonic_ns(), &mut self.outbox);
+                    self.flush();
+                }
+                return;
+            }
+            DlmRequest::NodeDown { .. } => CAP_ADMIN,
+            DlmRequest::Query { .. } => CAP_READ,
+            _ => CAP_WRITE,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        handle(&mut self.manager, message.sender, request, monotonic_ns(), &mut self.outbox);
+        self.flush();
+    }
+}
+
+fn main() {
+    let mut server = DlmServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
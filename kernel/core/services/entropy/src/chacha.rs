@@ -0,0 +1,204 @@
+/*
+ * Orion Operating System - ChaCha20 DRBG
+ *
+ * ChaCha20 block function (RFC 8439) and the deterministic random bit
+ * generator built on top of it. The generator uses fast key erasure: every
+ * request ends by overwriting the key with fresh keystream, so a later
+ * compromise of the state cannot reveal previously returned bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use orion_crypto::wipe;
+
+// ========================================
+// CHACHA20 CONSTANTS
+// ========================================
+
+pub const CHACHA_KEY_SIZE: usize = 32;
+pub const CHACHA_NONCE_SIZE: usize = 12;
+pub const CHACHA_BLOCK_SIZE: usize = 64;
+
+// "expand 32-byte k"
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// Maximum bytes served between two reseeds (1 MiB, well below the 2^38 limit)
+pub const RESEED_INTERVAL_BYTES: u64 = 1 << 20;
+
+#[inline(always)]
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// Apply the 20-round ChaCha permutation in place (no feed-forward)
+pub fn chacha_permute(state: &mut [u32; 16]) {
+    for _ in 0..10 {
+        // Column rounds
+        quarter_round(state, 0, 4, 8, 12);
+        quarter_round(state, 1, 5, 9, 13);
+        quarter_round(state, 2, 6, 10, 14);
+        quarter_round(state, 3, 7, 11, 15);
+        // Diagonal rounds
+        quarter_round(state, 0, 5, 10, 15);
+        quarter_round(state, 1, 6, 11, 12);
+        quarter_round(state, 2, 7, 8, 13);
+        quarter_round(state, 3, 4, 9, 14);
+    }
+}
+
+/// Compute one ChaCha20 keystream block
+pub fn chacha20_block(
+    key: &[u8; CHACHA_KEY_SIZE],
+    counter: u32,
+    nonce: &[u8; CHACHA_NONCE_SIZE],
+    out: &mut [u8; CHACHA_BLOCK_SIZE],
+) {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&SIGMA);
+    for i in 0..8 {
+        input[4 + i] = u32::from_le_bytes([key[i * 4], key[i * 4 + 1], key[i * 4 + 2], key[i * 4 + 3]]);
+    }
+    input[12] = counter;
+    for i in 0..3 {
+        input[13 + i] = u32::from_le_bytes([nonce[i * 4], nonce[i * 4 + 1], nonce[i * 4 + 2], nonce[i * 4 + 3]]);
+    }
+
+    let mut state = input;
+    chacha_permute(&mut state);
+
+    for i in 0..16 {
+        let word = state[i].wrapping_add(input[i]);
+        out[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+// ========================================
+// DETERMINISTIC RANDOM BIT GENERATOR
+// ========================================
+
+/// ChaCha20-based DRBG with fast key erasure
+pub struct ChaChaDrbg {
+    key: [u8; CHACHA_KEY_SIZE],
+    nonce: [u8; CHACHA_NONCE_SIZE],
+    seeded: bool,
+    reseed_count: u64,
+    bytes_since_reseed: u64,
+}
+
+impl ChaChaDrbg {
+    pub const fn new() -> Self {
+        Self {
+            key: [0; CHACHA_KEY_SIZE],
+            nonce: [0; CHACHA_NONCE_SIZE],
+            seeded: false,
+            reseed_count: 0,
+            bytes_since_reseed: 0,
+        }
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        self.seeded
+    }
+
+    pub fn reseed_count(&self) -> u64 {
+        self.reseed_count
+    }
+
+    /// Whether enough output has been produced that a reseed is due
+    pub fn needs_reseed(&self) -> bool {
+        self.bytes_since_reseed >= RESEED_INTERVAL_BYTES
+    }
+
+    /// Mix fresh seed material into the key.
+    ///
+    /// The new key is derived from the old key and the seed, so a weak seed
+    /// can never reduce the strength of an already seeded generator.
+    pub fn reseed(&mut self, seed: &[u8; CHACHA_KEY_SIZE]) {
+        let mut mixed = [0u8; CHACHA_KEY_SIZE];
+        for i in 0..CHACHA_KEY_SIZE {
+            mixed[i] = self.key[i] ^ seed[i];
+        }
+
+        let mut block = [0u8; CHACHA_BLOCK_SIZE];
+        chacha20_block(&mixed, 0, &self.nonce, &mut block);
+        self.key.copy_from_slice(&block[..CHACHA_KEY_SIZE]);
+        self.nonce.copy_from_slice(&block[CHACHA_KEY_SIZE..CHACHA_KEY_SIZE + CHACHA_NONCE_SIZE]);
+
+        wipe(&mut mixed);
+        wipe(&mut block);
+
+        self.seeded = true;
+        self.reseed_count += 1;
+        self.bytes_since_reseed = 0;
+    }
+
+    /// Fill `out` with random bytes, then erase the key used to produce them
+    pub fn generate(&mut self, out: &mut [u8]) {
+        let mut block = [0u8; CHACHA_BLOCK_SIZE];
+        let mut counter: u32 = 1;
+
+        for chunk in out.chunks_mut(CHACHA_BLOCK_SIZE) {
+            chacha20_block(&self.key, counter, &self.nonce, &mut block);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+            counter = counter.wrapping_add(1);
+        }
+
+        // Fast key erasure: block 0 is never handed out and becomes the next key
+        chacha20_block(&self.key, 0, &self.nonce, &mut block);
+        self.key.copy_from_slice(&block[..CHACHA_KEY_SIZE]);
+        wipe(&mut block);
+
+        self.bytes_since_reseed = self.bytes_since_reseed.saturating_add(out.len() as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc8439_block_vector() {
+        // RFC 8439, section 2.3.2
+        let mut key = [0u8; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let mut out = [0u8; 64];
+        chacha20_block(&key, 1, &nonce, &mut out);
+
+        assert_eq!(&out[..16], &[
+            0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15,
+            0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71, 0xc4,
+        ]);
+        assert_eq!(&out[48..], &[
+            0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9,
+            0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e,
+        ]);
+    }
+
+    #[test]
+    fn test_drbg_key_erasure() {
+        let mut drbg = ChaChaDrbg::new();
+        assert!(!drbg.is_seeded());
+        drbg.reseed(&[0x42; 32]);
+        assert!(drbg.is_seeded());
+
+        let mut first = [0u8; 100];
+        let mut second = [0u8; 100];
+        drbg.generate(&mut first);
+        drbg.generate(&mut second);
+        assert_ne!(first, second);
+    }
+}
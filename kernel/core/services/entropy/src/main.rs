@@ -0,0 +1,341 @@
+/*
+ * Orion Operating System - Entropy Server
+ *
+ * System-wide randomness service. Collects entropy from virtio-rng devices,
+ * RDSEED/RDRAND and interrupt timing jitter, conditions it in an input pool
+ * and serves getrandom()-style requests from a ChaCha20 DRBG. Requests that
+ * arrive before the generator is seeded block unless GRND_NONBLOCK is set.
+ * The server also registers /dev/random and /dev/urandom with devfs and
+ * serves them on its endpoint, reads waiting for the first seed as well
+ * (see random.rs).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_chardev::{CharDevice, CharRequest, DevfsClient, DeviceClass, Replies};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_cap::Capability;
+use orion_crypto::wipe;
+use orion_proto::reply;
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod chacha;
+mod pool;
+mod protocol;
+mod random;
+mod sources;
+
+use chacha::{ChaChaDrbg, CHACHA_KEY_SIZE};
+use pool::{EntropyPool, EntropySource};
+use protocol::*;
+use random::{RandomDevice, DEVICE_NAMES};
+use sources::{CpuRngFeatures, JitterCollector};
+
+/// Endpoint of the I/O server, the only peer allowed to register trusted sources
+const IO_SERVER_ENDPOINT: u64 = 2;
+
+/// Maximum number of trusted hardware RNG endpoints
+const MAX_TRUSTED_SOURCES: usize = 8;
+
+/// Maximum number of callers parked while waiting for the initial seed
+const MAX_PENDING_REQUESTS: usize = 64;
+
+/// CPU samples harvested on every idle iteration until seeded
+const CPU_HARVEST_WORDS: usize = 4;
+
+/// Endpoint serving the random devices, the server's own
+const ENDPOINT_NAME: &str = "entropy";
+
+struct PendingRequest {
+    sender: u64,
+    length: u32,
+}
+
+struct TrustedSource {
+    endpoint: u64,
+    source: EntropySource,
+}
+
+struct EntropyServer {
+    drbg: ChaChaDrbg,
+    pool: EntropyPool,
+    jitter: JitterCollector,
+    cpu_features: CpuRngFeatures,
+    trusted_sources: Vec<TrustedSource>,
+    pending: Vec<PendingRequest>,
+    bytes_served: u64,
+    device: CharDevice<RandomDevice>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl EntropyServer {
+    fn new() -> Self {
+        let mut server = Self {
+            drbg: ChaChaDrbg::new(),
+            pool: EntropyPool::new(),
+            jitter: JitterCollector::new(),
+            cpu_features: CpuRngFeatures::detect(),
+            trusted_sources: Vec::new(),
+            pending: Vec::new(),
+            bytes_served: 0,
+            device: CharDevice::new(RandomDevice::new()),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        };
+
+        // On machines with RDSEED this alone seeds the generator at startup
+        server.harvest_cpu(CHACHA_KEY_SIZE);
+        server.try_reseed();
+
+        server
+    }
+
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => {
+                    if !self.drbg.is_seeded() {
+                        self.harvest_cpu(CPU_HARVEST_WORDS);
+                    }
+                    self.try_reseed();
+                    self.ipc_channel.wait();
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        if CharRequest::decode(&message.data).is_some() {
+            self.handle_device(message.sender, &message.data);
+            return;
+        }
+
+        let request = match EntropyRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        match request {
+            EntropyRequest::GetRandom { length, flags } => {
+                self.handle_get_random(message.sender, length, flags);
+            }
+            EntropyRequest::AddEntropy { source, claimed_bits, data } => {
+                let status = self.handle_add_entropy(message.sender, source, claimed_bits, &data);
+                self.ipc_channel.send(message.sender, &reply(status, &[]));
+            }
+            EntropyRequest::IrqSample { irq, timestamp } => {
+                // Forwarded by the kernel interrupt path, no reply expected
+                self.jitter.add_sample(&mut self.pool, irq, timestamp);
+                self.try_reseed();
+            }
+            EntropyRequest::Status => {
+                let mut payload = Vec::new();
+                self.status().encode(&mut payload);
+                self.ipc_channel.send(message.sender, &reply(STATUS_OK, &payload));
+            }
+            EntropyRequest::RegisterSource { endpoint, source } => {
+                let status = self.handle_register_source(message.sender, endpoint, source);
+                self.ipc_channel.send(message.sender, &reply(status, &[]));
+            }
+        }
+    }
+
+    fn handle_get_random(&mut self, sender: u64, length: u32, flags: u32) {
+        let length = length.min(MAX_REQUEST_BYTES);
+
+        if self.drbg.is_seeded() || flags & GRND_INSECURE != 0 {
+            self.serve(sender, length);
+            return;
+        }
+
+        if flags & GRND_NONBLOCK != 0 || self.pending.len() >= MAX_PENDING_REQUESTS {
+            self.ipc_channel.send(sender, &reply(STATUS_EAGAIN, &[]));
+            return;
+        }
+
+        // Park the caller until the pool has gathered enough entropy
+        self.pending.push(PendingRequest { sender, length });
+    }
+
+    /// A request on /dev/random or /dev/urandom
+    fn handle_device(&mut self, sender: u64, request: &[u8]) {
+        if self.drbg.needs_reseed() {
+            self.harvest_cpu(CPU_HARVEST_WORDS);
+            self.try_reseed();
+        }
+        self.key_device();
+
+        let replies = self.device.handle(sender, request);
+        self.send(replies);
+
+        let mut stirred = self.device.driver_mut().take_stirred();
+        if !stirred.is_empty() {
+            self.pool.add(EntropySource::External, &stirred, 0);
+            wipe(&mut stirred);
+        }
+    }
+
+    /// Key the device from the seeded generator when it is due, and answer
+    /// the reads that waited for it
+    fn key_device(&mut self) {
+        let reseed_count = self.drbg.reseed_count();
+        if !self.drbg.is_seeded() || !self.device.driver().needs_key(reseed_count) {
+            return;
+        }
+
+        let mut key = [0u8; CHACHA_KEY_SIZE];
+        self.drbg.generate(&mut key);
+        self.device.driver_mut().rekey(&key, reseed_count);
+        wipe(&mut key);
+
+        let replies = self.device.wake();
+        self.send(replies);
+    }
+
+    fn send(&mut self, replies: Replies) {
+        for (recipient, mut response) in replies {
+            self.ipc_channel.send(recipient, &response);
+            wipe(&mut response);
+        }
+    }
+
+    fn handle_add_entropy(&mut self, sender: u64, source: u32, claimed_bits: u32, data: &[u8]) -> i32 {
+        let requested = match EntropySource::from_u32(source) {
+            Some(source) => source,
+            None => return STATUS_EINVAL,
+        };
+
+        // Anyone may stir the pool, but only registered devices earn credit
+        let trusted = self
+            .trusted_sources
+            .iter()
+            .any(|entry| entry.endpoint == sender && entry.source == requested);
+        let source = if trusted { requested } else { EntropySource::External };
+
+        self.pool.add(source, data, claimed_bits);
+        self.try_reseed();
+        STATUS_OK
+    }
+
+    fn handle_register_source(&mut self, sender: u64, endpoint: u64, source: u32) -> i32 {
+        if sender != IO_SERVER_ENDPOINT {
+            return STATUS_EPERM;
+        }
+
+        let source = match EntropySource::from_u32(source) {
+            Some(EntropySource::VirtioRng) => EntropySource::VirtioRng,
+            _ => return STATUS_EINVAL,
+        };
+
+        if self.trusted_sources.iter().any(|entry| entry.endpoint == endpoint) {
+            return STATUS_OK;
+        }
+        if self.trusted_sources.len() >= MAX_TRUSTED_SOURCES {
+            return STATUS_EAGAIN;
+        }
+
+        self.trusted_sources.push(TrustedSource { endpoint, source });
+        STATUS_OK
+    }
+
+    fn serve(&mut self, sender: u64, length: u32) {
+        if self.drbg.needs_reseed() {
+            self.harvest_cpu(CPU_HARVEST_WORDS);
+            self.try_reseed();
+        }
+
+        let mut output = vec![0u8; length as usize];
+        self.drbg.generate(&mut output);
+        self.bytes_served += length as u64;
+
+        self.ipc_channel.send(sender, &reply(STATUS_OK, &output));
+        wipe(&mut output);
+    }
+
+    fn harvest_cpu(&mut self, words: usize) {
+        sources::harvest_cpu(&mut self.pool, self.cpu_features, words);
+    }
+
+    /// Reseed the DRBG when the pool is full enough, then wake parked callers
+    fn try_reseed(&mut self) {
+        if !self.pool.can_seed() {
+            return;
+        }
+        if self.drbg.is_seeded() && !self.drbg.needs_reseed() {
+            return;
+        }
+
+        let mut seed = [0u8; CHACHA_KEY_SIZE];
+        self.pool.extract_seed(&mut seed);
+        self.drbg.reseed(&seed);
+        wipe(&mut seed);
+
+        let pending = core::mem::take(&mut self.pending);
+        for request in pending {
+            self.serve(request.sender, request.length);
+        }
+        self.key_device();
+    }
+
+    fn status(&self) -> EntropyStatus {
+        EntropyStatus {
+            seeded: self.drbg.is_seeded(),
+            pool_entropy_bits: self.pool.entropy_bits(),
+            reseed_count: self.drbg.reseed_count(),
+            bytes_served: self.bytes_served + self.device.driver().bytes_served(),
+            pending_requests: self.pending.len() as u32,
+            rdrand_available: self.cpu_features.rdrand,
+            rdseed_available: self.cpu_features.rdseed,
+        }
+    }
+}
+
+/// File system server channel, where the random devices are registered
+struct FsIpc(IpcChannel);
+
+impl orion_chardev::Transport for FsIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn main() {
+    let mut server = EntropyServer::new();
+    // getrandom() keeps working without the devices
+    let mut devfs = DevfsClient::new(FsIpc(IpcChannel::connect("fs")));
+    for name in DEVICE_NAMES {
+        let _ = devfs.register(name, DeviceClass::Rng, ENDPOINT_NAME);
+    }
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+/*
+ * Orion Operating System - Entropy Pool
+ *
+ * Input pool for the entropy service. Samples from all sources are absorbed
+ * into a sponge built on the ChaCha permutation; seed material for the DRBG
+ * is squeezed out of it once enough entropy has been credited.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use orion_crypto::wipe;
+
+use crate::chacha::{chacha_permute, CHACHA_KEY_SIZE};
+
+// ========================================
+// POOL CONSTANTS
+// ========================================
+
+/// Bits of credited entropy required before the DRBG is considered seeded
+pub const SEED_THRESHOLD_BITS: u32 = 256;
+
+/// The pool never claims to hold more entropy than its capacity
+const POOL_CAPACITY_BITS: u32 = 512;
+
+/// Rate portion of the sponge, in 32-bit words (the other 8 are capacity)
+const RATE_WORDS: usize = 8;
+
+// Entropy sources known to the service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySource {
+    VirtioRng = 0,
+    Rdseed = 1,
+    Rdrand = 2,
+    InterruptJitter = 3,
+    External = 4,
+}
+
+impl EntropySource {
+    pub const COUNT: usize = 5;
+
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(EntropySource::VirtioRng),
+            1 => Some(EntropySource::Rdseed),
+            2 => Some(EntropySource::Rdrand),
+            3 => Some(EntropySource::InterruptJitter),
+            4 => Some(EntropySource::External),
+            _ => None,
+        }
+    }
+
+    /// Upper bound on the entropy credited per input byte, in eighths of a bit.
+    ///
+    /// RDRAND output is mixed in but only credited at a quarter rate so that
+    /// a backdoored CPU can never seed the generator on its own.
+    pub fn max_credit_per_byte(self) -> u32 {
+        match self {
+            EntropySource::VirtioRng => 64,
+            EntropySource::Rdseed => 64,
+            EntropySource::Rdrand => 16,
+            EntropySource::InterruptJitter => 1,
+            EntropySource::External => 0,
+        }
+    }
+}
+
+// Per-source accounting, exposed through the status request
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceStats {
+    pub bytes_mixed: u64,
+    pub bits_credited: u64,
+    pub samples: u64,
+}
+
+// ========================================
+// ENTROPY POOL
+// ========================================
+
+pub struct EntropyPool {
+    state: [u32; 16],
+    rate_position: usize,
+    entropy_bits: u32,
+    total_credited: u64,
+    source_stats: [SourceStats; EntropySource::COUNT],
+}
+
+impl EntropyPool {
+    pub const fn new() -> Self {
+        Self {
+            state: [0; 16],
+            rate_position: 0,
+            entropy_bits: 0,
+            total_credited: 0,
+            source_stats: [SourceStats { bytes_mixed: 0, bits_credited: 0, samples: 0 }; EntropySource::COUNT],
+        }
+    }
+
+    /// Mix `data` into the pool and credit at most `claimed_bits` of entropy
+    pub fn add(&mut self, source: EntropySource, data: &[u8], claimed_bits: u32) {
+        self.absorb(data);
+
+        let ceiling = (data.len() as u32).saturating_mul(source.max_credit_per_byte()) / 8;
+        let credited = claimed_bits.min(ceiling);
+        self.entropy_bits = (self.entropy_bits + credited).min(POOL_CAPACITY_BITS);
+        self.total_credited += credited as u64;
+
+        let stats = &mut self.source_stats[source as usize];
+        stats.bytes_mixed += data.len() as u64;
+        stats.bits_credited += credited as u64;
+        stats.samples += 1;
+    }
+
+    pub fn entropy_bits(&self) -> u32 {
+        self.entropy_bits
+    }
+
+    pub fn total_credited(&self) -> u64 {
+        self.total_credited
+    }
+
+    pub fn can_seed(&self) -> bool {
+        self.entropy_bits >= SEED_THRESHOLD_BITS
+    }
+
+    pub fn source_stats(&self, source: EntropySource) -> SourceStats {
+        self.source_stats[source as usize]
+    }
+
+    /// Squeeze a DRBG seed out of the pool and debit its entropy estimate
+    pub fn extract_seed(&mut self, seed: &mut [u8; CHACHA_KEY_SIZE]) {
+        // Domain-separate extraction from absorption, then permute twice so the
+        // output is never the raw rate of a state that just absorbed input
+        self.state[RATE_WORDS] ^= 0x8000_0000;
+        chacha_permute(&mut self.state);
+        chacha_permute(&mut self.state);
+
+        for i in 0..RATE_WORDS {
+            seed[i * 4..i * 4 + 4].copy_from_slice(&self.state[i].to_le_bytes());
+        }
+
+        // Overwrite the rate so the seed cannot be recovered from the pool
+        for word in self.state[..RATE_WORDS].iter_mut() {
+            *word = 0;
+        }
+        chacha_permute(&mut self.state);
+        self.rate_position = 0;
+
+        self.entropy_bits = self.entropy_bits.saturating_sub(SEED_THRESHOLD_BITS);
+    }
+
+    fn absorb(&mut self, data: &[u8]) {
+        let mut word_buf = [0u8; 4];
+        for chunk in data.chunks(4) {
+            word_buf[..chunk.len()].copy_from_slice(chunk);
+            for byte in word_buf[chunk.len()..].iter_mut() {
+                *byte = 0;
+            }
+            self.state[self.rate_position] ^= u32::from_le_bytes(word_buf);
+            self.rate_position += 1;
+
+            if self.rate_position == RATE_WORDS {
+                chacha_permute(&mut self.state);
+                self.rate_position = 0;
+            }
+        }
+        wipe(&mut word_buf);
+
+        // Always finish with a permutation so partial blocks are diffused
+        if self.rate_position != 0 {
+            chacha_permute(&mut self.state);
+            self.rate_position = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit_is_capped_per_source() {
+        let mut pool = EntropyPool::new();
+        pool.add(EntropySource::Rdrand, &[0xAA; 32], 256);
+        // 32 bytes at 2 bits per byte
+        assert_eq!(pool.entropy_bits(), 64);
+
+        pool.add(EntropySource::External, &[0x55; 64], 512);
+        assert_eq!(pool.entropy_bits(), 64);
+        assert_eq!(pool.source_stats(EntropySource::External).bytes_mixed, 64);
+    }
+
+    #[test]
+    fn test_extract_debits_pool() {
+        let mut pool = EntropyPool::new();
+        pool.add(EntropySource::VirtioRng, &[0x11; 64], 512);
+        assert!(pool.can_seed());
+
+        let mut first = [0u8; 32];
+        let mut second = [0u8; 32];
+        pool.extract_seed(&mut first);
+        pool.extract_seed(&mut second);
+        assert_ne!(first, second);
+        assert!(!pool.can_seed());
+    }
+}
@@ -0,0 +1,111 @@
+/*
+ * Orion Operating System - Entropy Service Protocol
+ *
+ * Wire format of the requests understood by the entropy service. All fields
+ * are little-endian; every message starts with a 32-bit opcode and every
+ * reply starts with a 32-bit signed status (0 or a negative errno).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_GET_RANDOM: u32 = 1;
+pub const OP_ADD_ENTROPY: u32 = 2;
+pub const OP_IRQ_SAMPLE: u32 = 3;
+pub const OP_STATUS: u32 = 4;
+pub const OP_REGISTER_SOURCE: u32 = 5;
+
+// getrandom() flags (Linux compatible values)
+pub const GRND_NONBLOCK: u32 = 0x0001;
+pub const GRND_RANDOM: u32 = 0x0002;
+pub const GRND_INSECURE: u32 = 0x0004;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EINVAL: i32 = -22;
+
+/// Largest number of bytes returned by a single GET_RANDOM request; larger
+/// requests get a short read, exactly like getrandom(2)
+pub const MAX_REQUEST_BYTES: u32 = 4096;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EntropyRequest {
+    GetRandom { length: u32, flags: u32 },
+    AddEntropy { source: u32, claimed_bits: u32, data: Vec<u8> },
+    IrqSample { irq: u32, timestamp: u64 },
+    Status,
+    RegisterSource { endpoint: u64, source: u32 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+impl EntropyRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_GET_RANDOM => Some(EntropyRequest::GetRandom {
+                length: read_u32(data, 4)?,
+                flags: read_u32(data, 8)?,
+            }),
+            OP_ADD_ENTROPY => Some(EntropyRequest::AddEntropy {
+                source: read_u32(data, 4)?,
+                claimed_bits: read_u32(data, 8)?,
+                data: data.get(12..)?.to_vec(),
+            }),
+            OP_IRQ_SAMPLE => Some(EntropyRequest::IrqSample {
+                irq: read_u32(data, 4)?,
+                timestamp: read_u64(data, 8)?,
+            }),
+            OP_STATUS => Some(EntropyRequest::Status),
+            OP_REGISTER_SOURCE => Some(EntropyRequest::RegisterSource {
+                endpoint: read_u64(data, 4)?,
+                source: read_u32(data, 12)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Snapshot returned by OP_STATUS
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntropyStatus {
+    pub seeded: bool,
+    pub pool_entropy_bits: u32,
+    pub reseed_count: u64,
+    pub bytes_served: u64,
+    pub pending_requests: u32,
+    pub rdrand_available: bool,
+    pub rdseed_available: bool,
+}
+
+impl EntropyStatus {
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.push(self.seeded as u8);
+        out.push(self.rdrand_available as u8);
+        out.push(self.rdseed_available as u8);
+        out.push(0);
+        out.extend_from_slice(&self.pool_entropy_bits.to_le_bytes());
+        out.extend_from_slice(&self.reseed_count.to_le_bytes());
+        out.extend_from_slice(&self.bytes_served.to_le_bytes());
+        out.extend_from_slice(&self.pending_requests.to_le_bytes());
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * Orion Operating System - Random Devices
+ *
+ * /dev/random and /dev/urandom, registered with devfs as character
+ * devices served on the entropy server's endpoint (see lib/orion_chardev).
+ * The endpoint cannot tell two devices apart, so both names are the same
+ * device: a read waits until the generator has its first seed, as
+ * getrandom() without flags does, and never again afterwards. Its output
+ * comes from a DRBG of its own, keyed from the server's each time that one
+ * reseeds and after every reseed interval it served. Bytes written stir
+ * the pool without being credited.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_chardev::{CharDriver, CharError, Readiness};
+
+use crate::chacha::{ChaChaDrbg, CHACHA_KEY_SIZE};
+
+/// Names of the device in /dev
+pub const DEVICE_NAMES: [&str; 2] = ["random", "urandom"];
+
+pub struct RandomDevice {
+    drbg: ChaChaDrbg,
+    /// Reseeds of the server's generator when the device was last keyed
+    keyed_at: Option<u64>,
+    /// Written, for the server to stir into the pool
+    stirred: Vec<u8>,
+    bytes_served: u64,
+}
+
+impl RandomDevice {
+    pub fn new() -> Self {
+        Self { drbg: ChaChaDrbg::new(), keyed_at: None, stirred: Vec::new(), bytes_served: 0 }
+    }
+
+    /// Whether the device wants a key from a generator reseeded
+    /// `reseed_count` times
+    pub fn needs_key(&self, reseed_count: u64) -> bool {
+        self.keyed_at != Some(reseed_count) || self.drbg.needs_reseed()
+    }
+
+    /// Key the device from the output of the seeded server generator
+    pub fn rekey(&mut self, key: &[u8; CHACHA_KEY_SIZE], reseed_count: u64) {
+        self.drbg.reseed(key);
+        self.keyed_at = Some(reseed_count);
+    }
+
+    pub fn take_stirred(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.stirred)
+    }
+
+    pub fn bytes_served(&self) -> u64 {
+        self.bytes_served
+    }
+}
+
+impl CharDriver for RandomDevice {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, CharError> {
+        if self.keyed_at.is_none() {
+            return Err(CharError::WouldBlock);
+        }
+        self.drbg.generate(buffer);
+        self.bytes_served += buffer.len() as u64;
+        Ok(buffer.len())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize, CharError> {
+        self.stirred.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn poll(&mut self) -> Readiness {
+        match self.keyed_at {
+            Some(_) => Readiness::READABLE | Readiness::WRITABLE,
+            None => Readiness::WRITABLE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_chardev::{CharDevice, CharRequest, OPEN_NONBLOCK};
+
+    fn open(device: &mut CharDevice<RandomDevice>, sender: u64, flags: u32) -> u32 {
+        let replies = device.handle(sender, &CharRequest::Open { flags }.encode());
+        u32::from_le_bytes([replies[0].1[4], replies[0].1[5], replies[0].1[6], replies[0].1[7]])
+    }
+
+    fn status(response: &[u8]) -> i32 {
+        i32::from_le_bytes([response[0], response[1], response[2], response[3]])
+    }
+
+    #[test]
+    fn test_reads_wait_for_the_first_seed() {
+        let mut device = CharDevice::new(RandomDevice::new());
+        let blocking = open(&mut device, 10, 0);
+        let nonblocking = open(&mut device, 20, OPEN_NONBLOCK);
+
+        // Unseeded: a blocking read is parked, a non-blocking one refused
+        assert!(device.handle(10, &CharRequest::Read { handle: blocking, length: 32 }.encode()).is_empty());
+        let replies = device.handle(20, &CharRequest::Read { handle: nonblocking, length: 32 }.encode());
+        assert_eq!(status(&replies[0].1), CharError::WouldBlock.status());
+        assert!(device.wake().is_empty());
+
+        // Written bytes are only stirred in
+        let replies = device.handle(20, &CharRequest::Write { handle: nonblocking, data: b"noise".to_vec() }.encode());
+        assert_eq!(status(&replies[0].1), 0);
+        assert_eq!(device.driver_mut().take_stirred(), b"noise");
+
+        assert!(device.driver().needs_key(1));
+        device.driver_mut().rekey(&[0x42; CHACHA_KEY_SIZE], 1);
+        assert!(!device.driver().needs_key(1) && device.driver().needs_key(2));
+        let replies = device.wake();
+        assert_eq!(replies.len(), 1);
+        assert_eq!((replies[0].0, status(&replies[0].1), replies[0].1.len()), (10, 0, 4 + 32));
+
+        // Seeded: reads are answered at once, each with fresh bytes
+        let replies = device.handle(20, &CharRequest::Read { handle: nonblocking, length: 32 }.encode());
+        assert_eq!(status(&replies[0].1), 0);
+        let other = device.handle(10, &CharRequest::Read { handle: blocking, length: 32 }.encode());
+        assert_ne!(other[0].1[4..], replies[0].1[4..]);
+        assert_eq!(device.driver().bytes_served(), 96);
+    }
+}
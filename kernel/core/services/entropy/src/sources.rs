@@ -0,0 +1,200 @@
+/*
+ * Orion Operating System - Entropy Sources
+ *
+ * Collectors feeding the entropy pool: the CPU random number instructions
+ * (RDSEED/RDRAND) and interrupt timing jitter forwarded by the kernel.
+ * Hardware RNG devices (virtio-rng) push their output over IPC instead.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::pool::{EntropyPool, EntropySource};
+
+/// RDRAND/RDSEED can transiently fail under contention; retry this many times
+const CPU_RNG_RETRIES: u32 = 10;
+
+// ========================================
+// CPU RANDOM NUMBER INSTRUCTIONS
+// ========================================
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuRngFeatures {
+    pub rdrand: bool,
+    pub rdseed: bool,
+}
+
+impl CpuRngFeatures {
+    #[cfg(target_arch = "x86_64")]
+    pub fn detect() -> Self {
+        // CPUID.01H:ECX[30] = RDRAND, CPUID.07H.0:EBX[18] = RDSEED
+        let leaf1 = unsafe { core::arch::x86_64::__cpuid(1) };
+        let leaf7 = unsafe { core::arch::x86_64::__cpuid_count(7, 0) };
+        Self {
+            rdrand: leaf1.ecx & (1 << 30) != 0,
+            rdseed: leaf7.ebx & (1 << 18) != 0,
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn detect() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdseed64() -> Option<u64> {
+    for _ in 0..CPU_RNG_RETRIES {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdseed {0}",
+                "setc {1}",
+                out(reg) value,
+                out(reg_byte) ok,
+                options(nomem, nostack)
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdrand64() -> Option<u64> {
+    for _ in 0..CPU_RNG_RETRIES {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdrand {0}",
+                "setc {1}",
+                out(reg) value,
+                out(reg_byte) ok,
+                options(nomem, nostack)
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn rdseed64() -> Option<u64> {
+    None
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn rdrand64() -> Option<u64> {
+    None
+}
+
+/// Harvest `words` 64-bit samples from the CPU, preferring RDSEED.
+///
+/// Returns the number of samples actually mixed into the pool.
+pub fn harvest_cpu(pool: &mut EntropyPool, features: CpuRngFeatures, words: usize) -> usize {
+    let mut mixed = 0;
+
+    for _ in 0..words {
+        if features.rdseed {
+            if let Some(value) = rdseed64() {
+                pool.add(EntropySource::Rdseed, &value.to_le_bytes(), 64);
+                mixed += 1;
+                continue;
+            }
+        }
+        if features.rdrand {
+            if let Some(value) = rdrand64() {
+                pool.add(EntropySource::Rdrand, &value.to_le_bytes(), 64);
+                mixed += 1;
+            }
+        }
+    }
+
+    mixed
+}
+
+// ========================================
+// INTERRUPT TIMING JITTER
+// ========================================
+
+/// Estimates entropy in interrupt arrival times.
+///
+/// Every timestamp is mixed into the pool, but a sample is only credited one
+/// bit when its first, second and third order deltas are all non-zero, so
+/// periodic sources such as the timer tick earn nothing.
+pub struct JitterCollector {
+    last_timestamp: u64,
+    last_delta: i64,
+    last_delta2: i64,
+    samples: u64,
+    credited_samples: u64,
+}
+
+impl JitterCollector {
+    pub const fn new() -> Self {
+        Self {
+            last_timestamp: 0,
+            last_delta: 0,
+            last_delta2: 0,
+            samples: 0,
+            credited_samples: 0,
+        }
+    }
+
+    pub fn add_sample(&mut self, pool: &mut EntropyPool, irq: u32, timestamp: u64) {
+        let delta = timestamp.wrapping_sub(self.last_timestamp) as i64;
+        let delta2 = delta.wrapping_sub(self.last_delta);
+        let delta3 = delta2.wrapping_sub(self.last_delta2);
+
+        self.last_timestamp = timestamp;
+        self.last_delta = delta;
+        self.last_delta2 = delta2;
+        self.samples += 1;
+
+        let credit = if self.samples > 3 && delta != 0 && delta2 != 0 && delta3 != 0 {
+            self.credited_samples += 1;
+            1
+        } else {
+            0
+        };
+
+        let mut sample = [0u8; 12];
+        sample[..8].copy_from_slice(&timestamp.to_le_bytes());
+        sample[8..].copy_from_slice(&irq.to_le_bytes());
+        pool.add(EntropySource::InterruptJitter, &sample, credit);
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn credited_samples(&self) -> u64 {
+        self.credited_samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_periodic_interrupts_earn_no_credit() {
+        let mut pool = EntropyPool::new();
+        let mut jitter = JitterCollector::new();
+        for i in 0..64u64 {
+            jitter.add_sample(&mut pool, 0, i * 1000);
+        }
+        assert_eq!(jitter.credited_samples(), 0);
+        assert_eq!(pool.entropy_bits(), 0);
+    }
+}
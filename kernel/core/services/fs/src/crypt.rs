@@ -0,0 +1,617 @@
+/*
+ * Orion Operating System - File System Server Encrypted Directories
+ *
+ * Per-user encryption of data directories (see lib/orion_fscrypt). An
+ * encrypted directory is a directory of a mounted backend with a policy
+ * file at its top; EncryptedMount is stacked over it at a mount point of
+ * its own and encrypts every name and every file on the way down. The
+ * backend below only ever sees ciphertext, so a copy of its disk, or of
+ * the share an NFS server exports, tells nothing but sizes and layout.
+ *
+ * Keys live in the key table of the server. A session adds its key when
+ * the user logs in: it derives 64 bytes from a key of its keyring with
+ * DERIVE and purpose "fscrypt", and hands them over with ADD_KEY. The
+ * key is looked up on every call, never kept by open files, so removing
+ * it with REMOVE_KEY at logout makes the directory unreadable at once,
+ * open files included (ENOKEY). Nothing in the directory can be read
+ * again before a session of the user adds the key back.
+ *
+ *   ADD_KEY     key[64]                  -> identifier[16]
+ *   REMOVE_KEY  flags:u32 identifier[16] -> users:u32
+ *   KEY_STATUS  identifier[16]           -> present:u32 users:u32
+ *
+ * A key added by several sessions stays until the last of them removes
+ * it; REMOVE_KEY answers how many are left, ENOKEY for a sender that
+ * never added it. REMOVE_ALL_USERS removes it whoever added it, for
+ * CAP_ADMIN only. The backend below has no directories of its own to
+ * offer, so an encrypted directory holds files only.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use orion_crypto::aes::Aes256;
+use orion_crypto::wipe;
+use orion_fscrypt::contents::{self, read_header, write_header};
+use orion_fscrypt::{
+    decrypt_name, encrypt_name, ContentsError, FileHeader, KeyIdentifier, KeyTable, LowerFile, MasterKey, NameError,
+    Nonce, Policy, Removal, KEY_IDENTIFIER_SIZE, MASTER_KEY_SIZE, NONCE_SIZE, POLICY_FILE, POLICY_SIZE,
+};
+use orion_ipc::IpcChannel;
+use spin::Mutex;
+
+use crate::vfs::{DirEntry, FileAttributes, FileType, MountedFileSystem, OpenFlags, EINVAL, EIO, ENOKEY, ENOTEMPTY};
+
+// Opcodes, after the devfs requests
+pub const OP_FS_ADD_KEY: u32 = 0x4D;
+pub const OP_FS_REMOVE_KEY: u32 = 0x4E;
+pub const OP_FS_KEY_STATUS: u32 = 0x4F;
+
+/// REMOVE_KEY flag: remove the key for every session holding it
+pub const REMOVE_ALL_USERS: u32 = 1 << 0;
+
+// Reply status codes
+const STATUS_EPERM: i32 = -1;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOKEY: i32 = -126;
+
+/// Entropy GET_RANDOM request opcode (see services/entropy/src/protocol.rs)
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+
+/// Source of file nonces
+pub type NonceSource = fn() -> Result<Nonce, String>;
+
+/// A nonce from the entropy service. Files are created far less often
+/// than they are read or written, so each call connects anew
+pub fn entropy_nonce() -> Result<Nonce, String> {
+    let mut request = Vec::with_capacity(12);
+    request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+    request.extend_from_slice(&(NONCE_SIZE as u32).to_le_bytes());
+    request.extend_from_slice(&0u32.to_le_bytes());
+    let response = IpcChannel::connect("entropy").call(&request).map_err(|_| EIO.to_string())?;
+    if response.len() < 4 + NONCE_SIZE || response[..4] != 0i32.to_le_bytes() {
+        return Err(EIO.to_string());
+    }
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&response[4..4 + NONCE_SIZE]);
+    Ok(nonce)
+}
+
+/// The key identifier of the `key=<32 hex digits>` option, which sets up
+/// a new policy; None for a mount of an existing one, Err for anything
+/// else
+pub fn parse_options(options: &str) -> Result<Option<KeyIdentifier>, String> {
+    if options.is_empty() {
+        return Ok(None);
+    }
+    let hex = options.strip_prefix("key=").ok_or_else(|| EINVAL.to_string())?;
+    if hex.len() != 2 * KEY_IDENTIFIER_SIZE || !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(EINVAL.to_string());
+    }
+    let mut identifier = [0u8; KEY_IDENTIFIER_SIZE];
+    for (index, byte) in identifier.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).map_err(|_| EINVAL.to_string())?;
+    }
+    Ok(Some(identifier))
+}
+
+fn name_error(error: NameError) -> String {
+    match error {
+        NameError::TooLong => "File name too long".to_string(),
+        NameError::Invalid => EINVAL.to_string(),
+    }
+}
+
+fn contents_error(error: ContentsError<String>) -> String {
+    match error {
+        ContentsError::Lower(error) => error,
+        ContentsError::Corrupt | ContentsError::ShortWrite => EIO.to_string(),
+    }
+}
+
+/// An open file of the backend below
+struct Lower<'a> {
+    backend: &'a dyn MountedFileSystem,
+    file: u64,
+}
+
+impl LowerFile for Lower<'_> {
+    type Error = String;
+
+    fn read_at(&mut self, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+        self.backend.read_at(self.file, offset, buffer)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<usize, String> {
+        self.backend.write_at(self.file, offset, data)
+    }
+}
+
+struct EncryptedFile {
+    lower: u64,
+    /// None until the first write of a file created empty
+    header: Option<FileHeader>,
+}
+
+/// An encrypted directory of another backend, mounted on its own
+pub struct EncryptedMount {
+    lower: Arc<dyn MountedFileSystem>,
+    /// The directory in `lower`, "" for its root
+    base: String,
+    policy: Policy,
+    keys: Arc<Mutex<KeyTable>>,
+    nonces: NonceSource,
+    files: Mutex<BTreeMap<u64, EncryptedFile>>,
+    next_file: Mutex<u64>,
+}
+
+impl EncryptedMount {
+    /// Stack over the encrypted directory `base` of `lower`, by its
+    /// policy file; its key need not be in the table yet
+    pub fn attach(
+        lower: Arc<dyn MountedFileSystem>,
+        base: &str,
+        keys: Arc<Mutex<KeyTable>>,
+        nonces: NonceSource,
+    ) -> Result<Self, String> {
+        let base = base.trim_end_matches('/').to_string();
+        let file = lower.open(&alloc::format!("{}/{}", base, POLICY_FILE), OpenFlags::from_flags(0o1))?;
+        let mut data = [0u8; POLICY_SIZE];
+        let read = lower.read_at(file, 0, &mut data);
+        let _ = lower.close(file);
+        let policy = match read? {
+            POLICY_SIZE => Policy::decode(&data).ok_or_else(|| EINVAL.to_string())?,
+            _ => return Err(EINVAL.to_string()),
+        };
+        Ok(Self { lower, base, policy, keys, nonces, files: Mutex::new(BTreeMap::new()), next_file: Mutex::new(1) })
+    }
+
+    /// Make the empty directory `base` of `lower` an encrypted directory
+    /// for the key `identifier`, which must be in the table, and stack
+    /// over it
+    pub fn setup(
+        lower: Arc<dyn MountedFileSystem>,
+        base: &str,
+        identifier: KeyIdentifier,
+        keys: Arc<Mutex<KeyTable>>,
+        nonces: NonceSource,
+    ) -> Result<Self, String> {
+        if !keys.lock().contains(&identifier) {
+            return Err(ENOKEY.to_string());
+        }
+        let base = base.trim_end_matches('/');
+        let directory = if base.is_empty() { "/" } else { base };
+        if lower.read_directory(directory)?.iter().any(|entry| entry.name != "." && entry.name != "..") {
+            return Err(ENOTEMPTY.to_string());
+        }
+        // Exclusive, so two setups racing cannot both write a policy
+        let file = lower.open(&alloc::format!("{}/{}", base, POLICY_FILE), OpenFlags::from_flags(0o52))?;
+        let written = lower.write_at(file, 0, &Policy { key_identifier: identifier }.encode());
+        let synced = lower.sync(file);
+        let _ = lower.close(file);
+        match written? {
+            POLICY_SIZE => synced?,
+            _ => return Err(EIO.to_string()),
+        }
+        Self::attach(lower, base, keys, nonces)
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Path below of `path`, every component encrypted
+    fn lower_path(&self, names: &Aes256, path: &str) -> Result<String, String> {
+        let mut lower = self.base.clone();
+        for component in path.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => return Err(EINVAL.to_string()),
+                name => {
+                    lower.push('/');
+                    lower.push_str(&encrypt_name(names, name).map_err(name_error)?);
+                }
+            }
+        }
+        if lower.is_empty() {
+            lower.push('/');
+        }
+        Ok(lower)
+    }
+
+    /// Path below of `path`, ENOKEY while the key is absent
+    fn locate(&self, path: &str) -> Result<String, String> {
+        let keys = self.keys.lock();
+        let names = keys.names(&self.policy.key_identifier).ok_or_else(|| ENOKEY.to_string())?;
+        self.lower_path(names, path)
+    }
+
+    /// Header of the file at `path` below, None for an empty one
+    fn header_at(&self, lower_path: &str) -> Result<Option<FileHeader>, String> {
+        let file = self.lower.open(lower_path, OpenFlags::from_flags(0o1))?;
+        let header = read_header(&mut Lower { backend: &*self.lower, file }).map_err(contents_error);
+        let _ = self.lower.close(file);
+        header
+    }
+}
+
+impl MountedFileSystem for EncryptedMount {
+    fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String> {
+        let lower_path = self.locate(path)?;
+        // Writes rewrite whole units, so the file below is always read too
+        let lower_flags = OpenFlags::from_flags((flags.to_flags() | 0o1) & !0o200);
+        let file = self.lower.open(&lower_path, lower_flags)?;
+        let mut lower = Lower { backend: &*self.lower, file };
+        let header = match read_header(&mut lower) {
+            Ok(Some(mut header)) if flags.is_truncate() && flags.is_write() => {
+                header.size = 0;
+                write_header(&mut lower, &header).map(|_| Some(header))
+            }
+            result => result,
+        };
+        match header {
+            Ok(header) => {
+                let mut next_file = self.next_file.lock();
+                let id = *next_file;
+                *next_file += 1;
+                self.files.lock().insert(id, EncryptedFile { lower: file, header });
+                Ok(id)
+            }
+            Err(error) => {
+                let _ = self.lower.close(file);
+                Err(contents_error(error))
+            }
+        }
+    }
+
+    fn read_at(&self, file: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+        let files = self.files.lock();
+        let open = files.get(&file).ok_or_else(|| EINVAL.to_string())?;
+        let mut keys = self.keys.lock();
+        let Some(header) = open.header else {
+            return if keys.contains(&self.policy.key_identifier) { Ok(0) } else { Err(ENOKEY.to_string()) };
+        };
+        let cipher = keys.contents(&self.policy.key_identifier, &header.nonce).ok_or_else(|| ENOKEY.to_string())?;
+        let mut lower = Lower { backend: &*self.lower, file: open.lower };
+        contents::read(&mut lower, cipher, &header, offset, buffer).map_err(contents_error)
+    }
+
+    fn write_at(&self, file: u64, offset: u64, buffer: &[u8]) -> Result<usize, String> {
+        let mut files = self.files.lock();
+        let open = files.get_mut(&file).ok_or_else(|| EINVAL.to_string())?;
+        let mut lower = Lower { backend: &*self.lower, file: open.lower };
+        if open.header.is_none() {
+            if !self.keys.lock().contains(&self.policy.key_identifier) {
+                return Err(ENOKEY.to_string());
+            }
+            let header = FileHeader::new((self.nonces)()?);
+            write_header(&mut lower, &header).map_err(contents_error)?;
+            open.header = Some(header);
+        }
+        let header = open.header.as_mut().unwrap();
+        let mut keys = self.keys.lock();
+        let cipher = keys.contents(&self.policy.key_identifier, &header.nonce).ok_or_else(|| ENOKEY.to_string())?;
+        contents::write(&mut lower, cipher, header, offset, buffer).map_err(contents_error)
+    }
+
+    fn sync(&self, file: u64) -> Result<(), String> {
+        let lower = self.files.lock().get(&file).ok_or_else(|| EINVAL.to_string())?.lower;
+        self.lower.sync(lower)
+    }
+
+    fn close(&self, file: u64) -> Result<(), String> {
+        let open = self.files.lock().remove(&file).ok_or_else(|| EINVAL.to_string())?;
+        self.lower.close(open.lower)
+    }
+
+    fn get_attributes(&self, path: &str) -> Result<FileAttributes, String> {
+        let lower_path = self.locate(path)?;
+        let mut attributes = self.lower.get_attributes(&lower_path)?;
+        if attributes.file_type == FileType::Regular {
+            attributes.size = self.header_at(&lower_path)?.map_or(0, |header| header.size);
+        }
+        Ok(attributes)
+    }
+
+    fn read_directory(&self, path: &str) -> Result<Vec<DirEntry>, String> {
+        let lower_path = self.locate(path)?;
+        let entries = self.lower.read_directory(&lower_path)?;
+        let keys = self.keys.lock();
+        let names = keys.names(&self.policy.key_identifier).ok_or_else(|| ENOKEY.to_string())?;
+        // Anything not encrypted under this key, the policy file first,
+        // is left out
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| {
+                let name = decrypt_name(names, &entry.name).ok()?;
+                Some(DirEntry { name_len: name.len().min(u8::MAX as usize) as u8, name, ..entry })
+            })
+            .collect())
+    }
+
+    fn stacked_on(&self) -> Option<Arc<dyn MountedFileSystem>> {
+        Some(self.lower.clone())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyRequest {
+    Add { key: [u8; MASTER_KEY_SIZE] },
+    Remove { flags: u32, identifier: KeyIdentifier },
+    Status { identifier: KeyIdentifier },
+}
+
+fn read_identifier(data: &[u8], offset: usize) -> Option<KeyIdentifier> {
+    data.get(offset..offset + KEY_IDENTIFIER_SIZE)?.try_into().ok()
+}
+
+impl KeyRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let opcode = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+        match opcode {
+            OP_FS_ADD_KEY => Some(KeyRequest::Add { key: data.get(4..4 + MASTER_KEY_SIZE)?.try_into().ok()? }),
+            OP_FS_REMOVE_KEY => Some(KeyRequest::Remove {
+                flags: u32::from_le_bytes(data.get(4..8)?.try_into().ok()?),
+                identifier: read_identifier(data, 8)?,
+            }),
+            OP_FS_KEY_STATUS => Some(KeyRequest::Status { identifier: read_identifier(data, 4)? }),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for KeyRequest {
+    fn drop(&mut self) {
+        if let KeyRequest::Add { key } = self {
+            wipe(key);
+        }
+    }
+}
+
+/// Serve a key request from `sender`; the reply payload, or the status.
+/// `admin` is whether the sender holds CAP_ADMIN
+pub fn serve(keys: &Mutex<KeyTable>, sender: u64, admin: bool, request: KeyRequest) -> Result<Vec<u8>, i32> {
+    let mut keys = keys.lock();
+    match &request {
+        KeyRequest::Add { key } => {
+            let identifier = keys.add(MasterKey::new(*key), sender);
+            Ok(identifier.to_vec())
+        }
+        KeyRequest::Remove { flags, .. } if flags & !REMOVE_ALL_USERS != 0 => Err(STATUS_EINVAL),
+        KeyRequest::Remove { flags, identifier } if flags & REMOVE_ALL_USERS != 0 => {
+            if !admin {
+                return Err(STATUS_EPERM);
+            }
+            match keys.remove_all(identifier) {
+                true => Ok(0u32.to_le_bytes().to_vec()),
+                false => Err(STATUS_ENOKEY),
+            }
+        }
+        KeyRequest::Remove { identifier, .. } => match keys.remove(identifier, sender) {
+            Some(Removal::Removed) => Ok(0u32.to_le_bytes().to_vec()),
+            Some(Removal::OtherUsers(users)) => Ok((users as u32).to_le_bytes().to_vec()),
+            None => Err(STATUS_ENOKEY),
+        },
+        KeyRequest::Status { identifier } => {
+            let mut payload = (keys.contains(identifier) as u32).to_le_bytes().to_vec();
+            payload.extend_from_slice(&(keys.users(identifier) as u32).to_le_bytes());
+            Ok(payload)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeSet;
+    use alloc::vec;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    use crate::vfs::{FileSystemType, PathScope, VirtualFileSystem, EBUSY, EEXIST, ENOENT};
+
+    /// Backend keeping files and directories in memory, by full path
+    #[derive(Default)]
+    struct MemoryBackend {
+        directories: Mutex<BTreeSet<String>>,
+        files: Mutex<BTreeMap<String, Vec<u8>>>,
+        opened: Mutex<Vec<String>>,
+    }
+
+    impl MemoryBackend {
+        fn with_directory(path: &str) -> Arc<Self> {
+            let backend = Self::default();
+            backend.directories.lock().extend(["/".to_string(), path.to_string()]);
+            Arc::new(backend)
+        }
+    }
+
+    impl MountedFileSystem for MemoryBackend {
+        fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String> {
+            let mut files = self.files.lock();
+            match files.contains_key(path) {
+                true if flags.is_exclusive() => return Err(EEXIST.to_string()),
+                false if !flags.is_create() => return Err(ENOENT.to_string()),
+                _ => {}
+            }
+            files.entry(path.to_string()).or_default();
+            let mut opened = self.opened.lock();
+            opened.push(path.to_string());
+            Ok(opened.len() as u64 - 1)
+        }
+
+        fn read_at(&self, file: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+            let path = self.opened.lock()[file as usize].clone();
+            let files = self.files.lock();
+            let data = files[&path].get(offset as usize..).unwrap_or(&[]);
+            let length = data.len().min(buffer.len());
+            buffer[..length].copy_from_slice(&data[..length]);
+            Ok(length)
+        }
+
+        fn write_at(&self, file: u64, offset: u64, buffer: &[u8]) -> Result<usize, String> {
+            let path = self.opened.lock()[file as usize].clone();
+            let mut files = self.files.lock();
+            let data = files.get_mut(&path).unwrap();
+            data.resize(data.len().max(offset as usize + buffer.len()), 0);
+            data[offset as usize..offset as usize + buffer.len()].copy_from_slice(buffer);
+            Ok(buffer.len())
+        }
+
+        fn sync(&self, _file: u64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn close(&self, _file: u64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn get_attributes(&self, path: &str) -> Result<FileAttributes, String> {
+            if self.directories.lock().contains(path) {
+                return Ok(FileAttributes::new(1, FileType::Directory));
+            }
+            let files = self.files.lock();
+            let data = files.get(path).ok_or_else(|| ENOENT.to_string())?;
+            let mut attributes = FileAttributes::new(2, FileType::Regular);
+            attributes.size = data.len() as u64;
+            Ok(attributes)
+        }
+
+        fn read_directory(&self, path: &str) -> Result<Vec<DirEntry>, String> {
+            let prefix = alloc::format!("{}/", path.trim_end_matches('/'));
+            Ok(self
+                .files
+                .lock()
+                .keys()
+                .filter_map(|file| file.strip_prefix(&prefix).filter(|name| !name.contains('/')))
+                .enumerate()
+                .map(|(index, name)| DirEntry {
+                    name: name.to_string(),
+                    inode: 2,
+                    file_type: FileType::Regular,
+                    offset: index as u64,
+                    name_len: name.len() as u8,
+                })
+                .collect())
+        }
+    }
+
+    fn counter_nonce() -> Result<Nonce, String> {
+        static NEXT: AtomicU8 = AtomicU8::new(1);
+        Ok([NEXT.fetch_add(1, Ordering::Relaxed); NONCE_SIZE])
+    }
+
+    #[test]
+    fn test_encrypts_names_and_contents() {
+        let lower = MemoryBackend::with_directory("/alice");
+        let keys = Arc::new(Mutex::new(KeyTable::new()));
+        let identifier = keys.lock().add(MasterKey::new([7; 64]), 100);
+        let mount = EncryptedMount::setup(lower.clone(), "/alice/", identifier, keys.clone(), counter_nonce).unwrap();
+        assert_eq!(mount.policy().key_identifier, identifier);
+
+        let file = mount.open("/notes.txt", OpenFlags::from_flags(0o12)).unwrap();
+        assert_eq!(mount.write_at(file, 0, b"meet at the station").unwrap(), 19);
+        let mut buffer = [0u8; 32];
+        assert_eq!(mount.read_at(file, 8, &mut buffer).unwrap(), 11);
+        assert_eq!(&buffer[..11], b"the station");
+        mount.close(file).unwrap();
+
+        // Below, the policy in the clear and nothing else
+        let stored = lower.files.lock();
+        assert!(stored.contains_key("/alice/.orion-crypt"));
+        assert_eq!(stored.len(), 2);
+        for (path, data) in stored.iter() {
+            assert!(!path.contains("notes"));
+            assert!(!data.windows(7).any(|window| window == b"station"));
+        }
+        drop(stored);
+
+        let entries = mount.read_directory("/").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].name.as_str(), entries[0].name_len), ("notes.txt", 9));
+        assert_eq!(mount.get_attributes("/notes.txt").unwrap().size, 19);
+        assert_eq!(mount.open("/.orion-crypt", OpenFlags::from_flags(0o1)).unwrap_err(), ENOENT);
+
+        assert_eq!(
+            EncryptedMount::setup(lower.clone(), "/alice", identifier, keys.clone(), counter_nonce).err().unwrap(),
+            ENOTEMPTY
+        );
+        let unknown = MemoryBackend::with_directory("/bob");
+        assert_eq!(EncryptedMount::setup(unknown, "/bob", [1; 16], keys, counter_nonce).err().unwrap(), ENOKEY);
+    }
+
+    #[test]
+    fn test_removing_the_key_locks_open_files() {
+        let lower = MemoryBackend::with_directory("/alice");
+        let keys = Arc::new(Mutex::new(KeyTable::new()));
+        let identifier = keys.lock().add(MasterKey::new([7; 64]), 100);
+        let mount = EncryptedMount::setup(lower.clone(), "/alice", identifier, keys.clone(), counter_nonce).unwrap();
+        let file = mount.open("/diary", OpenFlags::from_flags(0o13)).unwrap();
+        mount.write_at(file, 0, b"dear diary").unwrap();
+
+        assert_eq!(serve(&keys, 100, false, KeyRequest::Remove { flags: 0, identifier }), Ok(vec![0, 0, 0, 0]));
+        let mut buffer = [0u8; 16];
+        assert_eq!(mount.read_at(file, 0, &mut buffer).unwrap_err(), ENOKEY);
+        assert_eq!(mount.write_at(file, 0, b"x").unwrap_err(), ENOKEY);
+        assert_eq!(mount.open("/diary", OpenFlags::from_flags(0o1)).unwrap_err(), ENOKEY);
+        assert_eq!(mount.read_directory("/").unwrap_err(), ENOKEY);
+        mount.close(file).unwrap();
+
+        // The next session adds the key back, and a new mount reads on
+        let payload = serve(&keys, 200, false, KeyRequest::Add { key: [7; 64] }).unwrap();
+        assert_eq!(payload, identifier);
+        let mount = EncryptedMount::attach(lower, "/alice", keys.clone(), counter_nonce).unwrap();
+        let file = mount.open("/diary", OpenFlags::from_flags(0o1)).unwrap();
+        assert_eq!(mount.read_at(file, 0, &mut buffer).unwrap(), 10);
+        assert_eq!(&buffer[..10], b"dear diary");
+    }
+
+    #[test]
+    fn test_keys_are_removed_by_their_users() {
+        let keys = Mutex::new(KeyTable::new());
+        let identifier: KeyIdentifier =
+            serve(&keys, 100, false, KeyRequest::Add { key: [3; 64] }).unwrap()[..].try_into().unwrap();
+        serve(&keys, 101, false, KeyRequest::Add { key: [3; 64] }).unwrap();
+        assert_eq!(serve(&keys, 102, false, KeyRequest::Remove { flags: 0, identifier }), Err(STATUS_ENOKEY));
+        assert_eq!(serve(&keys, 100, false, KeyRequest::Remove { flags: 0, identifier }), Ok(vec![1, 0, 0, 0]));
+        assert_eq!(serve(&keys, 0, false, KeyRequest::Status { identifier }), Ok(vec![1, 0, 0, 0, 1, 0, 0, 0]));
+
+        let all = KeyRequest::Remove { flags: REMOVE_ALL_USERS, identifier };
+        assert_eq!(serve(&keys, 1, false, all), Err(STATUS_EPERM));
+        let all = KeyRequest::Remove { flags: REMOVE_ALL_USERS, identifier };
+        assert_eq!(serve(&keys, 1, true, all), Ok(vec![0, 0, 0, 0]));
+        assert_eq!(serve(&keys, 0, false, KeyRequest::Status { identifier }), Ok(vec![0; 8]));
+
+        let mut request = OP_FS_REMOVE_KEY.to_le_bytes().to_vec();
+        request.extend_from_slice(&REMOVE_ALL_USERS.to_le_bytes());
+        request.extend_from_slice(&identifier);
+        assert_eq!(KeyRequest::decode(&request), Some(KeyRequest::Remove { flags: REMOVE_ALL_USERS, identifier }));
+        assert_eq!(KeyRequest::decode(&request[..20]), None);
+        assert_eq!(parse_options("key=000102030405060708090a0b0c0d0e0f"), Ok(Some(core::array::from_fn(|i| i as u8))));
+        assert!(parse_options("key=0001").is_err());
+    }
+
+    #[test]
+    fn test_lower_mounts_stay_while_stacked_upon() {
+        let vfs = VirtualFileSystem::new();
+        let lower = MemoryBackend::with_directory("/alice");
+        let keys = Arc::new(Mutex::new(KeyTable::new()));
+        let identifier = keys.lock().add(MasterKey::new([7; 64]), 100);
+        vfs.mount_with("/home", FileSystemType::NFS, "10.0.0.5:/home", "", lower).unwrap();
+
+        let (backend, base) = vfs.backend_at(PathScope::GLOBAL, "/home/alice").unwrap();
+        assert_eq!(base, "/alice");
+        let mount = Arc::new(EncryptedMount::setup(backend, &base, identifier, keys, counter_nonce).unwrap());
+        vfs.mount_with("/secure", FileSystemType::Encrypted, "/home/alice", "", mount).unwrap();
+        assert_eq!(vfs.unmount("/home").unwrap_err(), EBUSY);
+        vfs.unmount("/secure").unwrap();
+        vfs.unmount("/home").unwrap();
+    }
+}
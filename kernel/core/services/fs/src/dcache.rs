@@ -0,0 +1,296 @@
+/*
+ * Orion Operating System - Directory Entry Cache
+ *
+ * Caches the result of looking up one path component: the pair (parent
+ * directory inode, name) maps to the inode it names, or to a negative
+ * entry recording that the name does not exist. Negative entries make the
+ * repeated failed lookups of PATH-style searches as cheap as successful
+ * ones. Because entries are keyed by parent inode, renaming a directory
+ * does not stale the entries below it; only the names that changed are
+ * invalidated.
+ *
+ * Entries are evicted in least-recently-used order. Negative entries have
+ * their own, smaller limit so that a burst of misses cannot push out the
+ * positive working set.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::vfs::FileType;
+
+/// Result of a cached lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dentry {
+    Positive { inode: u64, file_type: FileType },
+    /// The name is known not to exist
+    Negative,
+}
+
+type DentryKey = (u64, String);
+
+struct CachedDentry {
+    dentry: Dentry,
+    last_used: u64,
+}
+
+// Dentry cache statistics, for tuning the limits
+#[derive(Debug, Clone, Default)]
+pub struct DcacheStatistics {
+    pub hits: u64,
+    pub negative_hits: u64,
+    pub misses: u64,
+    pub insertions: u64,
+    pub evictions: u64,
+    pub invalidations: u64,
+    pub entries: u64,
+    pub negative_entries: u64,
+    pub capacity: u64,
+    pub negative_capacity: u64,
+}
+
+pub struct DentryCache {
+    entries: BTreeMap<DentryKey, CachedDentry>,
+    // Recency order (tick -> key), one list per kind so each limit is cheap to enforce
+    positive_lru: BTreeMap<u64, DentryKey>,
+    negative_lru: BTreeMap<u64, DentryKey>,
+    tick: u64,
+    capacity: usize,
+    negative_capacity: usize,
+    statistics: DcacheStatistics,
+}
+
+impl DentryCache {
+    pub fn new(capacity: usize, negative_capacity: usize) -> Self {
+        let mut cache = Self {
+            entries: BTreeMap::new(),
+            positive_lru: BTreeMap::new(),
+            negative_lru: BTreeMap::new(),
+            tick: 0,
+            capacity: 0,
+            negative_capacity: 0,
+            statistics: DcacheStatistics::default(),
+        };
+        cache.set_limits(capacity, negative_capacity);
+        cache
+    }
+
+    /// Change the limits, evicting entries beyond them
+    pub fn set_limits(&mut self, capacity: usize, negative_capacity: usize) {
+        self.capacity = capacity;
+        self.negative_capacity = negative_capacity.min(capacity);
+        self.statistics.capacity = self.capacity as u64;
+        self.statistics.negative_capacity = self.negative_capacity as u64;
+        self.shrink();
+    }
+
+    fn lru_mut(&mut self, dentry: &Dentry) -> &mut BTreeMap<u64, DentryKey> {
+        match dentry {
+            Dentry::Positive { .. } => &mut self.positive_lru,
+            Dentry::Negative => &mut self.negative_lru,
+        }
+    }
+
+    /// Cached result for `name` in directory `parent`
+    pub fn lookup(&mut self, parent: u64, name: &str) -> Option<Dentry> {
+        let key = (parent, name.to_string());
+        self.tick += 1;
+        let tick = self.tick;
+
+        let (dentry, last_used) = match self.entries.get_mut(&key) {
+            Some(cached) => {
+                let last_used = cached.last_used;
+                cached.last_used = tick;
+                (cached.dentry, last_used)
+            }
+            None => {
+                self.statistics.misses += 1;
+                return None;
+            }
+        };
+
+        let lru = self.lru_mut(&dentry);
+        lru.remove(&last_used);
+        lru.insert(tick, key);
+        match dentry {
+            Dentry::Positive { .. } => self.statistics.hits += 1,
+            Dentry::Negative => self.statistics.negative_hits += 1,
+        }
+        Some(dentry)
+    }
+
+    /// Record the result of a lookup that missed the cache
+    pub fn insert(&mut self, parent: u64, name: &str, dentry: Dentry) {
+        if self.capacity == 0 || (dentry == Dentry::Negative && self.negative_capacity == 0) {
+            return;
+        }
+        self.remove((parent, name.to_string()));
+
+        self.tick += 1;
+        let tick = self.tick;
+        let key = (parent, name.to_string());
+        self.lru_mut(&dentry).insert(tick, key.clone());
+        self.entries.insert(key, CachedDentry { dentry, last_used: tick });
+        self.statistics.insertions += 1;
+        self.shrink();
+    }
+
+    /// Drop the entry for `name` in `parent` (unlink, rename, create)
+    pub fn invalidate(&mut self, parent: u64, name: &str) {
+        if self.remove((parent, name.to_string())) {
+            self.statistics.invalidations += 1;
+        }
+    }
+
+    /// Drop every entry of directory `parent` (mount and unmount on it)
+    pub fn invalidate_directory(&mut self, parent: u64) {
+        let keys: Vec<DentryKey> = self
+            .entries
+            .range((parent, String::new())..)
+            .take_while(|((directory, _), _)| *directory == parent)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.remove(key);
+            self.statistics.invalidations += 1;
+        }
+    }
+
+    /// Drop everything, e.g. when a mount changes the shape of the tree
+    pub fn invalidate_all(&mut self) {
+        self.statistics.invalidations += self.entries.len() as u64;
+        self.entries.clear();
+        self.positive_lru.clear();
+        self.negative_lru.clear();
+        self.update_counts();
+    }
+
+    fn remove(&mut self, key: DentryKey) -> bool {
+        match self.entries.remove(&key) {
+            Some(cached) => {
+                self.lru_mut(&cached.dentry).remove(&cached.last_used);
+                self.update_counts();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Evict least recently used entries until both limits hold
+    fn shrink(&mut self) {
+        while self.negative_lru.len() > self.negative_capacity {
+            self.evict_oldest(true);
+        }
+        while self.entries.len() > self.capacity {
+            let oldest_negative = self.negative_lru.keys().next().copied();
+            let oldest_positive = self.positive_lru.keys().next().copied();
+            let negative = match (oldest_negative, oldest_positive) {
+                (Some(negative), Some(positive)) => negative < positive,
+                (Some(_), None) => true,
+                _ => false,
+            };
+            self.evict_oldest(negative);
+        }
+        self.update_counts();
+    }
+
+    fn evict_oldest(&mut self, negative: bool) {
+        let lru = if negative { &mut self.negative_lru } else { &mut self.positive_lru };
+        if let Some((_, key)) = lru.pop_first() {
+            self.entries.remove(&key);
+            self.statistics.evictions += 1;
+        }
+    }
+
+    fn update_counts(&mut self) {
+        self.statistics.entries = self.entries.len() as u64;
+        self.statistics.negative_entries = self.negative_lru.len() as u64;
+    }
+
+    pub fn statistics(&self) -> DcacheStatistics {
+        self.statistics.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positive(inode: u64) -> Dentry {
+        Dentry::Positive { inode, file_type: FileType::Regular }
+    }
+
+    #[test]
+    fn test_caches_positive_and_negative_entries() {
+        let mut cache = DentryCache::new(16, 4);
+        assert_eq!(cache.lookup(1, "bin"), None);
+        cache.insert(1, "bin", positive(2));
+        cache.insert(2, "ls", Dentry::Negative);
+
+        assert_eq!(cache.lookup(1, "bin"), Some(positive(2)));
+        assert_eq!(cache.lookup(2, "ls"), Some(Dentry::Negative));
+
+        // Creating the file replaces the negative entry
+        cache.invalidate(2, "ls");
+        assert_eq!(cache.lookup(2, "ls"), None);
+
+        let statistics = cache.statistics();
+        assert_eq!((statistics.hits, statistics.negative_hits, statistics.misses), (1, 1, 2));
+        assert_eq!((statistics.entries, statistics.negative_entries, statistics.invalidations), (1, 0, 1));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = DentryCache::new(3, 2);
+        cache.insert(1, "a", positive(2));
+        cache.insert(1, "b", positive(3));
+        cache.insert(1, "c", positive(4));
+        assert!(cache.lookup(1, "a").is_some());
+
+        // "b" is the least recently used
+        cache.insert(1, "d", positive(5));
+        assert_eq!(cache.lookup(1, "b"), None);
+        assert!(cache.lookup(1, "a").is_some());
+
+        // Negative entries are bounded separately and evicted first
+        for name in ["x", "y", "z"] {
+            cache.insert(1, name, Dentry::Negative);
+        }
+        let statistics = cache.statistics();
+        assert_eq!((statistics.entries, statistics.negative_entries), (3, 2));
+        assert_eq!(cache.lookup(1, "x"), None);
+        assert_eq!(cache.lookup(1, "z"), Some(Dentry::Negative));
+
+        cache.set_limits(1, 0);
+        assert_eq!(cache.statistics().entries, 1);
+        cache.insert(1, "w", Dentry::Negative);
+        assert_eq!(cache.lookup(1, "w"), None);
+    }
+
+    #[test]
+    fn test_invalidates_directories() {
+        let mut cache = DentryCache::new(16, 8);
+        cache.insert(1, "mnt", positive(2));
+        cache.insert(2, "a", positive(3));
+        cache.insert(2, "b", Dentry::Negative);
+        cache.insert(3, "c", positive(4));
+
+        cache.invalidate_directory(2);
+        assert_eq!(cache.lookup(2, "a"), None);
+        assert_eq!(cache.lookup(2, "b"), None);
+        assert!(cache.lookup(1, "mnt").is_some());
+        assert!(cache.lookup(3, "c").is_some());
+
+        cache.invalidate_all();
+        assert_eq!(cache.statistics().entries, 0);
+    }
+}
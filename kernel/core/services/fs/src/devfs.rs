@@ -0,0 +1,80 @@
+/*
+ * Orion Operating System - Device File System
+ *
+ * The character devices in /dev registered by their drivers (see
+ * lib/orion_chardev/src/devfs.rs). Each registration creates the node
+ * /dev/<name> and remembers which process registered it and the IPC
+ * endpoint serving it, for programs to look up; unregistering removes
+ * the node again.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_chardev::devfs::encode_lookup;
+use orion_chardev::{DevfsRequest, DeviceClass};
+use spin::RwLock;
+
+use crate::vfs::{FileType, VirtualFileSystem};
+
+// Reply status codes
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EEXIST: i32 = -17;
+
+struct DeviceNode {
+    owner: u64,
+    class: DeviceClass,
+    endpoint: String,
+}
+
+pub struct DeviceTable {
+    /// By device name
+    nodes: RwLock<BTreeMap<String, DeviceNode>>,
+}
+
+impl DeviceTable {
+    pub fn new() -> Self {
+        Self { nodes: RwLock::new(BTreeMap::new()) }
+    }
+
+    /// Serve a request from `sender`; the reply payload, or the status
+    pub fn serve(&self, vfs: &VirtualFileSystem, sender: u64, request: DevfsRequest) -> Result<Vec<u8>, i32> {
+        let path = request.path();
+        match request {
+            DevfsRequest::Register { name, class, endpoint } => {
+                let mut nodes = self.nodes.write();
+                match nodes.get_mut(&name) {
+                    // Its driver again, serving it on another endpoint
+                    Some(node) if node.owner == sender => {
+                        node.class = class;
+                        node.endpoint = endpoint;
+                    }
+                    Some(_) => return Err(STATUS_EEXIST),
+                    None => {
+                        vfs.create(&path, FileType::CharacterDevice).map_err(|_| STATUS_EEXIST)?;
+                        nodes.insert(name, DeviceNode { owner: sender, class, endpoint });
+                    }
+                }
+                Ok(Vec::new())
+            }
+            DevfsRequest::Unregister { name } => {
+                self.nodes.write().remove(&name).ok_or(STATUS_ENOENT)?;
+                // Gone already if someone unlinked it
+                let _ = vfs.remove(&path);
+                Ok(Vec::new())
+            }
+            DevfsRequest::Lookup { name } => {
+                let nodes = self.nodes.read();
+                let node = nodes.get(&name).ok_or(STATUS_ENOENT)?;
+                Ok(encode_lookup(node.class, &node.endpoint))
+            }
+        }
+    }
+}
@@ -0,0 +1,501 @@
+/*
+ * Orion Operating System - File System Server Direct File Requests
+ *
+ * Positioned file access for servers that move file contents through IPC
+ * rather than a ring, such as the NBD server exporting image files. A
+ * file is opened once by path; reads and writes name their offset and
+ * never move the file position, and each request holds the lock of its
+ * open file like ring entries do, so the two paths stay ordered.
+ *
+ *   OPEN      flags:u32 path                  -> handle:u32 size:u64
+ *   READ_AT   handle:u32 offset:u64 length:u32 -> data
+ *   WRITE_AT  handle:u32 offset:u64 data...    -> written:u32
+ *   SYNC      handle:u32                       -> (empty)
+ *   CLOSE     handle:u32                       -> (empty)
+ *   LINK_AT   handle:u32 path                  -> (empty)
+ *   RENAME    flags:u32 old_path new_path      -> (empty)
+ *
+ * `flags` of OPEN are the open flags of the VFS (0o1 read, 0o2 write,
+ * 0o10 to create the file when it is missing, 0o40 to fail with EEXIST
+ * when it is not, 0o200 for an unnamed file in the directory `path`).
+ * Paths are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Requests changing a directory hold its lock in the worker pool (see
+ * workers.rs): a creating OPEN the lock of the parent, LINK_AT the one
+ * of the directory receiving the name, RENAME both, so concurrent
+ * clients see each change whole and in arrival order. An unnamed file
+ * gets its name with LINK_AT, which fails with EEXIST rather than
+ * replace a file, and is gone when closed without one. RENAME flags are
+ * those of the VFS (RENAME_NOREPLACE).
+ *
+ * Requests naming a path are also checked against the MAC policy (see
+ * capabilities/mac.h), on the path as the sender names it with "." and
+ * ".." taken out: opening needs the permissions its flags ask for,
+ * creating a name `create` on it, and renaming `write` on the old name.
+ * Symbolic links are followed after the check, so a policy labels the
+ * paths programs use rather than where links lead.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_async::spawn_blocking;
+use orion_mac::{PERM_CREATE, PERM_READ, PERM_WRITE};
+
+use crate::vfs::{self, FileType, OpenFlags, PathScope, VirtualFileSystem};
+use crate::workers::WorkerPool;
+
+// Opcodes, after WORKER_STATS
+pub const OP_FS_OPEN: u32 = 0x41;
+pub const OP_FS_READ_AT: u32 = 0x42;
+pub const OP_FS_WRITE_AT: u32 = 0x43;
+pub const OP_FS_SYNC: u32 = 0x44;
+pub const OP_FS_CLOSE: u32 = 0x45;
+// After the mount requests of main.rs
+pub const OP_FS_LINK_AT: u32 = 0x4A;
+pub const OP_FS_RENAME: u32 = 0x4B;
+
+/// Largest READ_AT length, bounded by what one IPC reply carries
+pub const MAX_TRANSFER: u32 = 64 * 1024;
+
+// Reply status codes
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EIO: i32 = -5;
+const STATUS_EBADF: i32 = -9;
+const STATUS_EEXIST: i32 = -17;
+const STATUS_ENOTDIR: i32 = -20;
+const STATUS_EISDIR: i32 = -21;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOTEMPTY: i32 = -39;
+const STATUS_ENOKEY: i32 = -126;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileRequest {
+    Open { flags: u32, path: String },
+    ReadAt { handle: u32, offset: u64, length: u32 },
+    WriteAt { handle: u32, offset: u64, data: Vec<u8> },
+    Sync { handle: u32 },
+    Close { handle: u32 },
+    LinkAt { handle: u32, path: String },
+    Rename { flags: u32, old_path: String, new_path: String },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, utf-8 bytes` field and return it with the offset after it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let length = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + length)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + length))
+}
+
+/// `path` without empty, "." and ".." components, as the MAC policy
+/// sees it; ".." stops at the root
+fn mac_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    let mut normalized = String::with_capacity(path.len());
+    for component in components {
+        normalized.push('/');
+        normalized.push_str(component);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Reply status for an error of the VFS
+fn status_of(error: String) -> i32 {
+    match error.as_str() {
+        vfs::EEXIST => STATUS_EEXIST,
+        vfs::ENOTDIR => STATUS_ENOTDIR,
+        vfs::EISDIR => STATUS_EISDIR,
+        vfs::EINVAL => STATUS_EINVAL,
+        vfs::ENOTEMPTY => STATUS_ENOTEMPTY,
+        vfs::EBADF => STATUS_EBADF,
+        vfs::EIO => STATUS_EIO,
+        vfs::ENOKEY => STATUS_ENOKEY,
+        _ => STATUS_ENOENT,
+    }
+}
+
+impl FileRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_FS_OPEN => Some(FileRequest::Open { flags: read_u32(data, 4)?, path: read_string(data, 8)?.0 }),
+            OP_FS_READ_AT => Some(FileRequest::ReadAt {
+                handle: read_u32(data, 4)?,
+                offset: read_u64(data, 8)?,
+                length: read_u32(data, 16)?,
+            }),
+            OP_FS_WRITE_AT => Some(FileRequest::WriteAt {
+                handle: read_u32(data, 4)?,
+                offset: read_u64(data, 8)?,
+                data: data.get(16..)?.to_vec(),
+            }),
+            OP_FS_SYNC => Some(FileRequest::Sync { handle: read_u32(data, 4)? }),
+            OP_FS_CLOSE => Some(FileRequest::Close { handle: read_u32(data, 4)? }),
+            OP_FS_LINK_AT => Some(FileRequest::LinkAt { handle: read_u32(data, 4)?, path: read_string(data, 8)?.0 }),
+            OP_FS_RENAME => {
+                let (old_path, next) = read_string(data, 8)?;
+                let (new_path, _) = read_string(data, next)?;
+                Some(FileRequest::Rename { flags: read_u32(data, 4)?, old_path, new_path })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the request opens the file for writing or changes a directory
+    pub fn writes(&self) -> bool {
+        match self {
+            FileRequest::Open { flags, .. } => OpenFlags::from_flags(*flags).is_write(),
+            FileRequest::LinkAt { .. } | FileRequest::Rename { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Paths checked against the MAC policy before serving the request,
+    /// with the permissions each needs
+    pub fn mac_accesses(&self) -> Vec<(String, u32)> {
+        match self {
+            FileRequest::Open { flags, path } => {
+                let flags = OpenFlags::from_flags(*flags);
+                let mut perms = if flags.is_write() { PERM_WRITE } else { 0 };
+                if flags.is_read() || perms == 0 {
+                    perms |= PERM_READ;
+                }
+                if flags.is_create() || flags.is_tmpfile() {
+                    perms |= PERM_CREATE;
+                }
+                vec![(mac_path(path), perms)]
+            }
+            FileRequest::LinkAt { path, .. } => vec![(mac_path(path), PERM_CREATE)],
+            FileRequest::Rename { old_path, new_path, .. } => {
+                vec![(mac_path(old_path), PERM_WRITE), (mac_path(new_path), PERM_CREATE)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the sender's capability is checked before serving it:
+    /// requests naming a path, as handles were checked when opened
+    pub fn names_path(&self) -> bool {
+        matches!(self, FileRequest::Open { .. } | FileRequest::LinkAt { .. } | FileRequest::Rename { .. })
+    }
+}
+
+/// Serve a request, opening paths in the mount namespace of `scope`; the
+/// reply payload on success, a negative status otherwise
+pub async fn serve<T>(
+    pool: &WorkerPool<T>,
+    vfs: &Arc<VirtualFileSystem>,
+    scope: PathScope,
+    request: FileRequest,
+) -> Result<Vec<u8>, i32> {
+    let vfs = vfs.clone();
+    let handle = match request {
+        FileRequest::Open { flags, path } => {
+            let (handle, size) = open(pool, vfs, scope, OpenFlags::from_flags(flags), path).await?;
+            let mut payload = Vec::with_capacity(12);
+            payload.extend_from_slice(&(handle as u32).to_le_bytes());
+            payload.extend_from_slice(&size.to_le_bytes());
+            return Ok(payload);
+        }
+        FileRequest::Rename { flags, old_path, new_path } => {
+            rename(pool, vfs, scope, flags, old_path, new_path).await?;
+            return Ok(Vec::new());
+        }
+        FileRequest::ReadAt { handle, .. }
+        | FileRequest::WriteAt { handle, .. }
+        | FileRequest::Sync { handle }
+        | FileRequest::Close { handle }
+        | FileRequest::LinkAt { handle, .. } => handle as u64,
+    };
+
+    let lock = pool.file_lock(handle);
+    let _order = pool.order_file(&lock).await;
+    match request {
+        FileRequest::ReadAt { offset, length, .. } => {
+            if length > MAX_TRANSFER {
+                return Err(STATUS_EINVAL);
+            }
+            spawn_blocking(move || {
+                let mut data = vec![0u8; length as usize];
+                let read = vfs.read_at(handle, offset, &mut data).map_err(|_| STATUS_EBADF)?;
+                data.truncate(read);
+                Ok(data)
+            })
+            .await
+        }
+        FileRequest::WriteAt { offset, data, .. } => {
+            let written =
+                spawn_blocking(move || vfs.write_at(handle, offset, &data)).await.map_err(|_| STATUS_EBADF)?;
+            Ok((written as u32).to_le_bytes().to_vec())
+        }
+        FileRequest::Sync { .. } => {
+            spawn_blocking(move || vfs.sync(handle)).await.map_err(|_| STATUS_EBADF)?;
+            Ok(Vec::new())
+        }
+        // Holds the file's lock too, so a write submitted before the link
+        // is in the file once it has a name
+        FileRequest::LinkAt { path, .. } => {
+            let parent = {
+                let vfs = vfs.clone();
+                let path = path.clone();
+                spawn_blocking(move || vfs.parent_at(scope, &path)).await.map_err(status_of)?
+            };
+            let directory = pool.directory_lock(parent);
+            let result = {
+                let _changing = pool.order_directory(&directory).await;
+                spawn_blocking(move || vfs.link_at(handle, scope, &path)).await.map_err(status_of)
+            };
+            pool.release_directory(parent, directory);
+            result.map(|_| Vec::new())
+        }
+        _ => {
+            spawn_blocking(move || vfs.close(handle)).await.map_err(|_| STATUS_EBADF)?;
+            pool.forget_file(handle);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Open `path`, creating it or an unnamed file as `flags` ask; the
+/// handle and the size of the file
+async fn open<T>(
+    pool: &WorkerPool<T>,
+    vfs: Arc<VirtualFileSystem>,
+    scope: PathScope,
+    flags: OpenFlags,
+    path: String,
+) -> Result<(u64, u64), i32> {
+    if flags.is_tmpfile() {
+        let handle = spawn_blocking(move || vfs.open_tmpfile_at(scope, &path, flags)).await.map_err(status_of)?;
+        return Ok((handle, 0));
+    }
+    if !flags.is_create() {
+        return spawn_blocking(move || {
+            let attributes = vfs.get_attributes_at(scope, &path, 0).map_err(status_of)?;
+            if attributes.file_type == FileType::Directory {
+                return Err(STATUS_EISDIR);
+            }
+            let handle = vfs.open_at(scope, &path, flags).map_err(status_of)?;
+            Ok((handle, attributes.size))
+        })
+        .await;
+    }
+
+    let parent = {
+        let vfs = vfs.clone();
+        let path = path.clone();
+        spawn_blocking(move || vfs.parent_at(scope, &path)).await.map_err(status_of)?
+    };
+    let directory = pool.directory_lock(parent);
+    let result = {
+        let _changing = pool.order_directory(&directory).await;
+        spawn_blocking(move || {
+            let (handle, created) = vfs.create_open_at(scope, &path, flags).map_err(status_of)?;
+            if created {
+                return Ok((handle, 0));
+            }
+            // An existing name may be a directory, which cannot be opened as a file
+            match vfs.get_attributes_at(scope, &path, 0) {
+                Ok(attributes) if attributes.file_type != FileType::Directory => Ok((handle, attributes.size)),
+                Ok(_) => {
+                    let _ = vfs.close(handle);
+                    Err(STATUS_EISDIR)
+                }
+                Err(error) => {
+                    let _ = vfs.close(handle);
+                    Err(status_of(error))
+                }
+            }
+        })
+        .await
+    };
+    pool.release_directory(parent, directory);
+    result
+}
+
+/// Rename under the locks of both parent directories, taken in inode
+/// order after the rename lock when they differ. A rename that finds its
+/// directories moved once it holds them resolves the paths again
+async fn rename<T>(
+    pool: &WorkerPool<T>,
+    vfs: Arc<VirtualFileSystem>,
+    scope: PathScope,
+    flags: u32,
+    old_path: String,
+    new_path: String,
+) -> Result<(), i32> {
+    loop {
+        let parents = {
+            let (vfs, old_path, new_path) = (vfs.clone(), old_path.clone(), new_path.clone());
+            spawn_blocking(move || -> Result<(u64, u64), String> {
+                Ok((vfs.parent_at(scope, &old_path)?, vfs.parent_at(scope, &new_path)?))
+            })
+            .await
+            .map_err(status_of)?
+        };
+        let (first, second) = (parents.0.min(parents.1), parents.0.max(parents.1));
+        let first_lock = pool.directory_lock(first);
+        let second_lock = (second != first).then(|| pool.directory_lock(second));
+        let result = {
+            let _rename = if second_lock.is_some() { Some(pool.order_rename().await) } else { None };
+            let _first = pool.order_directory(&first_lock).await;
+            let _second = match &second_lock {
+                Some(lock) => Some(pool.order_directory(lock).await),
+                None => None,
+            };
+            let (vfs, old_path, new_path) = (vfs.clone(), old_path.clone(), new_path.clone());
+            spawn_blocking(move || vfs.rename_locked(scope, &old_path, &new_path, flags, parents)).await
+        };
+        pool.release_directory(first, first_lock);
+        if let Some(lock) = second_lock {
+            pool.release_directory(second, lock);
+        }
+        match result {
+            Err(error) if error == vfs::ESTALE => continue,
+            result => return result.map_err(status_of),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::FileSystemType;
+    use orion_async::block_on;
+
+    #[test]
+    fn test_serves_positioned_requests() {
+        let vfs = Arc::new(VirtualFileSystem::new());
+        vfs.mount("/", FileSystemType::RamFS, "ram0", "defaults").unwrap();
+        vfs.create("/disk.img", FileType::Regular).unwrap();
+        let pool = WorkerPool::<()>::new(1);
+        let scope = PathScope::GLOBAL;
+
+        let mut open = OP_FS_OPEN.to_le_bytes().to_vec();
+        open.extend_from_slice(&0o3u32.to_le_bytes());
+        open.extend_from_slice(&9u32.to_le_bytes());
+        open.extend_from_slice(b"/disk.img");
+        let request = FileRequest::decode(&open).unwrap();
+        assert!(request.writes());
+        let payload = block_on(serve(&pool, &vfs, scope, request)).unwrap();
+        assert_eq!(payload.len(), 12);
+        let handle = read_u32(&payload, 0).unwrap();
+
+        let mut write = OP_FS_WRITE_AT.to_le_bytes().to_vec();
+        write.extend_from_slice(&handle.to_le_bytes());
+        write.extend_from_slice(&4096u64.to_le_bytes());
+        write.extend_from_slice(&[0xAA; 512]);
+        let written = block_on(serve(&pool, &vfs, scope, FileRequest::decode(&write).unwrap())).unwrap();
+        assert_eq!(written, 512u32.to_le_bytes());
+
+        let read = FileRequest::ReadAt { handle, offset: 0, length: MAX_TRANSFER + 1 };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, read)), Err(STATUS_EINVAL));
+        assert_eq!(block_on(serve(&pool, &vfs, scope, FileRequest::Sync { handle })), Ok(Vec::new()));
+        assert_eq!(block_on(serve(&pool, &vfs, scope, FileRequest::Close { handle })), Ok(Vec::new()));
+        assert_eq!(block_on(serve(&pool, &vfs, scope, FileRequest::Sync { handle })), Err(STATUS_EBADF));
+
+        let missing = FileRequest::Open { flags: 0o1, path: String::from("/missing") };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, missing)), Err(STATUS_ENOENT));
+        let created = FileRequest::Open { flags: 0o13, path: String::from("/missing") };
+        let payload = block_on(serve(&pool, &vfs, scope, created)).unwrap();
+        assert_eq!(read_u64(&payload, 4), Some(0));
+        let directory = FileRequest::Open { flags: 0o1, path: String::from("/") };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, directory)), Err(STATUS_EISDIR));
+    }
+
+    #[test]
+    fn test_serves_directory_changes() {
+        let vfs = Arc::new(VirtualFileSystem::new());
+        vfs.create("/spool", FileType::Directory).unwrap();
+        vfs.create("/done", FileType::Directory).unwrap();
+        let pool = WorkerPool::<()>::new(1);
+        let scope = PathScope::GLOBAL;
+
+        let exclusive = FileRequest::Open { flags: 0o52, path: String::from("/spool/job") };
+        let handle = read_u32(&block_on(serve(&pool, &vfs, scope, exclusive)).unwrap(), 0).unwrap();
+        let again = FileRequest::Open { flags: 0o52, path: String::from("/spool/job") };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, again)), Err(STATUS_EEXIST));
+        assert_eq!(block_on(serve(&pool, &vfs, scope, FileRequest::Close { handle })), Ok(Vec::new()));
+
+        // Written unnamed, then published under a name nobody else holds
+        let tmpfile = FileRequest::Open { flags: 0o202, path: String::from("/spool") };
+        let handle = read_u32(&block_on(serve(&pool, &vfs, scope, tmpfile)).unwrap(), 0).unwrap();
+        let mut link = OP_FS_LINK_AT.to_le_bytes().to_vec();
+        link.extend_from_slice(&handle.to_le_bytes());
+        link.extend_from_slice(&10u32.to_le_bytes());
+        link.extend_from_slice(b"/spool/job");
+        let request = FileRequest::decode(&link).unwrap();
+        assert!(request.writes() && request.names_path());
+        assert_eq!(block_on(serve(&pool, &vfs, scope, request)), Err(STATUS_EEXIST));
+        let request = FileRequest::LinkAt { handle, path: String::from("/spool/next") };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, request)), Ok(Vec::new()));
+        assert_eq!(block_on(serve(&pool, &vfs, scope, FileRequest::Close { handle })), Ok(Vec::new()));
+
+        let mut rename = OP_FS_RENAME.to_le_bytes().to_vec();
+        rename.extend_from_slice(&vfs::RENAME_NOREPLACE.to_le_bytes());
+        for path in ["/spool/job", "/spool/next"] {
+            rename.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            rename.extend_from_slice(path.as_bytes());
+        }
+        let request = FileRequest::decode(&rename).unwrap();
+        assert_eq!(block_on(serve(&pool, &vfs, scope, request)), Err(STATUS_EEXIST));
+        let request =
+            FileRequest::Rename { flags: 0, old_path: String::from("/spool/job"), new_path: String::from("/done/job") };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, request)), Ok(Vec::new()));
+        let request =
+            FileRequest::Rename { flags: 0, old_path: String::from("/done"), new_path: String::from("/done/x") };
+        assert_eq!(block_on(serve(&pool, &vfs, scope, request)), Err(STATUS_EINVAL));
+        assert!(vfs.get_attributes("/done/job").is_ok());
+
+        let statistics = pool.statistics();
+        assert!(statistics.directory_acquisitions >= 6);
+        assert_eq!(statistics.directory_contended, 0);
+    }
+
+    #[test]
+    fn test_names_mac_accesses() {
+        let open = FileRequest::Open { flags: 0o13, path: String::from("/srv//www/./../www/index") };
+        assert_eq!(open.mac_accesses(), [(String::from("/srv/www/index"), PERM_READ | PERM_WRITE | PERM_CREATE)]);
+        let escape = FileRequest::Open { flags: 0, path: String::from("/srv/www/../../../etc/shadow") };
+        assert_eq!(escape.mac_accesses(), [(String::from("/etc/shadow"), PERM_READ)]);
+
+        let rename =
+            FileRequest::Rename { flags: 0, old_path: String::from("/spool/job"), new_path: String::from("/done/") };
+        assert_eq!(
+            rename.mac_accesses(),
+            [(String::from("/spool/job"), PERM_WRITE), (String::from("/done"), PERM_CREATE)]
+        );
+        assert!(FileRequest::Close { handle: 1 }.mac_accesses().is_empty());
+    }
+}
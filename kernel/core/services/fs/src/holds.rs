@@ -0,0 +1,302 @@
+/*
+ * Orion Operating System - Device Holds of Mounts
+ *
+ * A mount may depend on a device driven through the I/O server: MOUNT
+ * then names its handle, and the server takes a hold on it for as long as
+ * the mount exists (DEVICE_HOLD and DEVICE_RELEASE, see
+ * services/io/src/protocol.rs). The driver of a held device is only
+ * unloaded when forced; the I/O server then sends DEVICE_REVOKED and the
+ * calls on the mount fail with EIO until it is unmounted. A mount shared
+ * by several namespaces gives its hold back once the last of them lets
+ * it go.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+use spin::Mutex;
+
+use crate::vfs::{DirEntry, FileAttributes, MountedFileSystem, OpenFlags, EIO};
+
+// I/O server requests and events (mirror of services/io/src/protocol.rs)
+const IO_OP_DEVICE_HOLD: u32 = 15;
+const IO_OP_DEVICE_RELEASE: u32 = 16;
+const IO_EVENT_DEVICE_REVOKED: u32 = 0x100;
+
+const STATUS_EIO: i32 = -5;
+
+/// Takes a hold on a device for a purpose, returning its identifier
+pub type HoldCall = fn(u64, &str) -> Result<u32, i32>;
+/// Gives a hold back
+pub type ReleaseCall = fn(u32);
+
+/// DEVICE_HOLD at the I/O server. Mounts are rare, so each call connects anew
+pub fn io_hold(device: u64, purpose: &str) -> Result<u32, i32> {
+    let mut request = Vec::with_capacity(16 + purpose.len());
+    request.extend_from_slice(&IO_OP_DEVICE_HOLD.to_le_bytes());
+    request.extend_from_slice(&device.to_le_bytes());
+    request.extend_from_slice(&(purpose.len() as u32).to_le_bytes());
+    request.extend_from_slice(purpose.as_bytes());
+    let response = IpcChannel::connect("io").call(&request).map_err(|_| STATUS_EIO)?;
+    match response.get(..4).map(|status| i32::from_le_bytes([status[0], status[1], status[2], status[3]])) {
+        Some(0) => {
+            response.get(4..8).map(|hold| u32::from_le_bytes([hold[0], hold[1], hold[2], hold[3]])).ok_or(STATUS_EIO)
+        }
+        Some(status) => Err(status),
+        None => Err(STATUS_EIO),
+    }
+}
+
+/// DEVICE_RELEASE at the I/O server
+pub fn io_release(hold: u32) {
+    let mut request = Vec::with_capacity(8);
+    request.extend_from_slice(&IO_OP_DEVICE_RELEASE.to_le_bytes());
+    request.extend_from_slice(&hold.to_le_bytes());
+    let _ = IpcChannel::connect("io").call(&request);
+}
+
+/// Device handle and hold of a DEVICE_REVOKED event. Its opcode is the
+/// one of a ring registration, so only a message from the I/O server is
+/// taken for one
+pub fn decode_revoked(data: &[u8]) -> Option<(u64, u32)> {
+    if data.len() != 16 || data[..4] != IO_EVENT_DEVICE_REVOKED.to_le_bytes() {
+        return None;
+    }
+    let mut device = [0u8; 8];
+    device.copy_from_slice(&data[4..12]);
+    Some((u64::from_le_bytes(device), u32::from_le_bytes([data[12], data[13], data[14], data[15]])))
+}
+
+/// The holds the server has on devices
+pub struct DeviceHolds {
+    /// Device of every hold still standing, by hold
+    held: Mutex<BTreeMap<u32, u64>>,
+    hold: HoldCall,
+    release: ReleaseCall,
+}
+
+impl DeviceHolds {
+    pub fn new(hold: HoldCall, release: ReleaseCall) -> Self {
+        Self { held: Mutex::new(BTreeMap::new()), hold, release }
+    }
+
+    pub fn hold(&self, device: u64, purpose: &str) -> Result<u32, i32> {
+        let hold = (self.hold)(device, purpose)?;
+        self.held.lock().insert(hold, device);
+        Ok(hold)
+    }
+
+    /// Give a hold back, unless the I/O server revoked it meanwhile
+    pub fn release(&self, hold: u32) {
+        if self.held.lock().remove(&hold).is_some() {
+            (self.release)(hold);
+        }
+    }
+
+    /// Forget a hold the I/O server revoked; false if it is not one of ours
+    pub fn revoke(&self, device: u64, hold: u32) -> bool {
+        let mut held = self.held.lock();
+        if held.get(&hold) != Some(&device) {
+            return false;
+        }
+        held.remove(&hold);
+        true
+    }
+
+    pub fn is_held(&self, hold: u32) -> bool {
+        self.held.lock().contains_key(&hold)
+    }
+}
+
+/// Hold `device` for the mount of `backend` at `path`. When the device
+/// cannot be held, the backend is let go and the I/O server's status
+/// returned
+pub fn held(
+    holds: &Arc<DeviceHolds>,
+    backend: Arc<dyn MountedFileSystem>,
+    device: u64,
+    path: &str,
+) -> Result<Arc<dyn MountedFileSystem>, i32> {
+    match holds.hold(device, &format!("mount {}", path)) {
+        Ok(hold) => Ok(Arc::new(HeldMount { inner: backend, holds: holds.clone(), hold })),
+        Err(status) => {
+            backend.unmount();
+            Err(status)
+        }
+    }
+}
+
+/// A mounted backend whose device is held while it is mounted
+pub struct HeldMount {
+    inner: Arc<dyn MountedFileSystem>,
+    holds: Arc<DeviceHolds>,
+    hold: u32,
+}
+
+impl HeldMount {
+    /// Calls fail once the device was revoked
+    fn check(&self) -> Result<(), String> {
+        if self.holds.is_held(self.hold) {
+            Ok(())
+        } else {
+            Err(EIO.to_string())
+        }
+    }
+}
+
+impl MountedFileSystem for HeldMount {
+    fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String> {
+        self.check()?;
+        self.inner.open(path, flags)
+    }
+
+    fn read_at(&self, file: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+        self.check()?;
+        self.inner.read_at(file, offset, buffer)
+    }
+
+    fn write_at(&self, file: u64, offset: u64, buffer: &[u8]) -> Result<usize, String> {
+        self.check()?;
+        self.inner.write_at(file, offset, buffer)
+    }
+
+    fn sync(&self, file: u64) -> Result<(), String> {
+        self.check()?;
+        self.inner.sync(file)
+    }
+
+    // Files are still closed, so that the mount can go
+    fn close(&self, file: u64) -> Result<(), String> {
+        self.inner.close(file)
+    }
+
+    fn get_attributes(&self, path: &str) -> Result<FileAttributes, String> {
+        self.check()?;
+        self.inner.get_attributes(path)
+    }
+
+    fn read_directory(&self, path: &str) -> Result<Vec<DirEntry>, String> {
+        self.check()?;
+        self.inner.read_directory(path)
+    }
+
+    fn unmount(&self) {
+        self.inner.unmount();
+        self.holds.release(self.hold);
+    }
+
+    fn stacked_on(&self) -> Option<Arc<dyn MountedFileSystem>> {
+        self.inner.stacked_on()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use crate::vfs::{FileSystemType, FileType, VirtualFileSystem, INITIAL_NAMESPACE};
+
+    static NEXT_HOLD: AtomicU32 = AtomicU32::new(1);
+    static RELEASED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+    /// Holds anything but device 0, which no driver drives
+    fn fake_hold(device: u64, _purpose: &str) -> Result<u32, i32> {
+        match device {
+            0 => Err(-2),
+            _ => Ok(NEXT_HOLD.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+
+    fn fake_release(hold: u32) {
+        RELEASED.lock().push(hold);
+    }
+
+    fn released(hold: u32) -> usize {
+        RELEASED.lock().iter().filter(|released| **released == hold).count()
+    }
+
+    struct EmptyBackend;
+
+    impl MountedFileSystem for EmptyBackend {
+        fn open(&self, _path: &str, _flags: OpenFlags) -> Result<u64, String> {
+            Ok(1)
+        }
+        fn read_at(&self, _file: u64, _offset: u64, _buffer: &mut [u8]) -> Result<usize, String> {
+            Ok(0)
+        }
+        fn write_at(&self, _file: u64, _offset: u64, buffer: &[u8]) -> Result<usize, String> {
+            Ok(buffer.len())
+        }
+        fn sync(&self, _file: u64) -> Result<(), String> {
+            Ok(())
+        }
+        fn close(&self, _file: u64) -> Result<(), String> {
+            Ok(())
+        }
+        fn get_attributes(&self, _path: &str) -> Result<FileAttributes, String> {
+            Ok(FileAttributes::new(1, FileType::Directory))
+        }
+        fn read_directory(&self, _path: &str) -> Result<Vec<DirEntry>, String> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_releases_the_hold_with_the_last_mount() {
+        let holds = Arc::new(DeviceHolds::new(fake_hold, fake_release));
+        let vfs = VirtualFileSystem::new();
+        vfs.mount("/", FileSystemType::RamFS, "ram0", "defaults").unwrap();
+        vfs.create("/mnt", FileType::Directory).unwrap();
+
+        assert_eq!(held(&holds, Arc::new(EmptyBackend), 0, "/mnt").err(), Some(-2));
+        let mount = held(&holds, Arc::new(EmptyBackend), 0x10, "/mnt").unwrap();
+        vfs.mount_in(INITIAL_NAMESPACE, "/mnt", FileSystemType::NFS, "disk", "", mount).unwrap();
+        let hold = *holds.held.lock().keys().next().unwrap();
+
+        // A child namespace shares the mount: its hold stays
+        vfs.create_namespace(7, INITIAL_NAMESPACE, "/").unwrap();
+        vfs.unmount_in(INITIAL_NAMESPACE, "/mnt").unwrap();
+        assert!(holds.is_held(hold));
+        vfs.drop_namespace(7).unwrap();
+        assert!(!holds.is_held(hold));
+        assert_eq!(released(hold), 1);
+    }
+
+    #[test]
+    fn test_fails_calls_once_revoked() {
+        let holds = Arc::new(DeviceHolds::new(fake_hold, fake_release));
+        let mount = held(&holds, Arc::new(EmptyBackend), 0x20, "/data").unwrap();
+        let hold = *holds.held.lock().keys().next().unwrap();
+        assert_eq!(mount.open("/file", OpenFlags::new()), Ok(1));
+
+        // Only the pair the I/O server gave out revokes it
+        assert!(!holds.revoke(0x21, hold) && !holds.revoke(0x20, hold + 1000));
+        assert!(holds.revoke(0x20, hold));
+        assert_eq!(mount.open("/file", OpenFlags::new()), Err(EIO.to_string()));
+        assert_eq!(mount.read_directory("/").err(), Some(EIO.to_string()));
+        assert_eq!(mount.close(1), Ok(()));
+
+        // The device is gone: nothing to give back
+        mount.unmount();
+        assert_eq!(released(hold), 0);
+
+        let mut event = vec![0u8, 1, 0, 0];
+        event.extend_from_slice(&0x20u64.to_le_bytes());
+        event.extend_from_slice(&hold.to_le_bytes());
+        assert_eq!(decode_revoked(&event), Some((0x20, hold)));
+        assert_eq!(decode_revoked(&event[..12]), None);
+        assert_eq!(decode_revoked(&0x100u32.to_le_bytes()), None);
+    }
+}
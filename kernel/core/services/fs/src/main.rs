@@ -16,31 +16,211 @@
 
 extern crate alloc;
 
-use orion_ipc::IpcChannel;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use orion_fscrypt::KeyTable;
+use orion_ipc::{IpcChannel, IpcMessage};
 use orion_cap::Capability;
+use orion_chardev::DevfsRequest;
+use orion_health::HealthChecks;
+use orion_mac::CLASS_FILE;
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+use orion_ring::RingRequest;
+use spin::Mutex;
 
 // Global allocator for the server
-use linked_list_allocator::LockedHeap;
+use orion_alloc::OrionHeap;
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the VFS tree and the dentry cache
+const HEAP_SIZE: usize = 8 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
 
+mod crypt;
+mod dcache;
+mod devfs;
+mod files;
+mod holds;
+mod nfs;
+mod rings;
 mod vfs;
+mod workers;
+
+use crypt::{EncryptedMount, KeyRequest};
+use devfs::DeviceTable;
+use files::FileRequest;
+use holds::DeviceHolds;
+use nfs::NfsMount;
+use rings::RingTable;
+use vfs::{VirtualFileSystem, FileSystemType, FileType, MountedFileSystem, PathScope, EINVAL, ENOENT, ENOKEY, ENOTEMPTY, INITIAL_NAMESPACE};
+use workers::WorkerPool;
+
+/// Worker tasks serving requests; raise it for workloads with many
+/// independent files and a blocking backend with several helper threads
+const FS_WORKERS: usize = workers::DEFAULT_WORKERS;
+
+/// How long the receive loop lets the workers run before polling the
+/// channel again while requests are in flight
+const RECEIVE_POLL_NS: u64 = 50_000;
+
+// Server requests, clear of the ring protocol range
+//
+//   WORKER_STATS   -> workers:u32 in_flight:u32 statistics (u64 fields)
+//   MOUNT          type:u32 path source options [device:u64] -> (empty)
+//   UNMOUNT        path                         -> (empty)
+//   NAMESPACE      namespace:u32 root           -> (empty)
+//   NAMESPACE_DROP namespace:u32                -> (empty)
+//
+// MOUNT attaches a network share (MOUNT_NFS, see nfs.rs for the source
+// and options) or an encrypted directory (MOUNT_ENCRYPTED, see crypt.rs),
+// and UNMOUNT detaches a mount point, EBUSY while files are open on it or
+// an encrypted directory is mounted from it. Both act on the mount
+// namespace of the sender. The source of MOUNT_ENCRYPTED is the directory
+// holding the files, on a mounted backend; its options are empty to
+// mount an encrypted directory, or `key=<identifier in hex>` to make an
+// empty one encrypted with that key first. A mount whose backend needs a
+// device driven through the I/O server names its handle, and the device
+// is held for as long as the mount exists (see holds.rs).
+//
+// NAMESPACE sets up the mount table of a kernel mount namespace the
+// sender made for a child (see orion-run): a copy of the sender's own,
+// rooted at its directory `root`, EEXIST if the namespace has one.
+// NAMESPACE_DROP forgets it once the child is gone. A namespace nobody
+// set up gets a copy of its parent's table when it first sends a request.
+// All four need CAP_ADMIN. Strings are a `len: u32` followed by UTF-8
+// bytes.
+//
+// The devfs requests of character device drivers, DEVFS_REGISTER,
+// DEVFS_UNREGISTER and DEVFS_LOOKUP (0x4A-0x4C), are described in
+// lib/orion_chardev/src/devfs.rs and served by devfs.rs.
+//
+// The key requests of encrypted directories, ADD_KEY, REMOVE_KEY and
+// KEY_STATUS (0x4D-0x4F), are described and served in crypt.rs; they
+// need CAP_READ, and removing a key for everyone CAP_ADMIN.
+//
+// DEVICE_REVOKED events of the I/O server, for the device of a mount
+// whose driver was unloaded by force, are taken in before any request.
+//
+// The CHECK request of the health server (see orion_health) is answered
+// before any other: the server is ready once its root is mounted.
+const OP_FS_WORKER_STATS: u32 = 0x40;
+const OP_FS_MOUNT: u32 = 0x46;
+const OP_FS_UNMOUNT: u32 = 0x47;
+const OP_FS_NAMESPACE: u32 = 0x48;
+const OP_FS_NAMESPACE_DROP: u32 = 0x49;
+
+// MOUNT types
+const MOUNT_NFS: u32 = 1;
+const MOUNT_ENCRYPTED: u32 = 2;
 
-use vfs::{VirtualFileSystem, FileSystemType, FileType};
+// Reply status codes
+const STATUS_OK: i32 = 0;
+const STATUS_EPERM: i32 = -1;
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EIO: i32 = -5;
+const STATUS_EBUSY: i32 = -16;
+const STATUS_EEXIST: i32 = -17;
+const STATUS_EINVAL: i32 = -22;
+const STATUS_ENOTEMPTY: i32 = -39;
+const STATUS_ENOKEY: i32 = -126;
+
+enum MountRequest {
+    Mount { fs_type: u32, path: String, source: String, options: String, device: u64 },
+    Unmount { path: String },
+    Namespace { namespace: u32, root: String },
+    DropNamespace { namespace: u32 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decode a `len, utf-8 bytes` field and return it with the offset after it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + len))
+}
+
+impl MountRequest {
+    fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_FS_MOUNT => {
+                let (path, next) = read_string(data, 8)?;
+                let (source, next) = read_string(data, next)?;
+                let (options, next) = read_string(data, next)?;
+                // No device unless one is named
+                let device = match data.get(next..) {
+                    Some([]) => 0,
+                    Some(&[a, b, c, d, e, f, g, h]) => u64::from_le_bytes([a, b, c, d, e, f, g, h]),
+                    _ => return None,
+                };
+                Some(MountRequest::Mount { fs_type: read_u32(data, 4)?, path, source, options, device })
+            }
+            OP_FS_UNMOUNT => Some(MountRequest::Unmount { path: read_string(data, 4)?.0 }),
+            OP_FS_NAMESPACE => {
+                Some(MountRequest::Namespace { namespace: read_u32(data, 4)?, root: read_string(data, 8)?.0 })
+            }
+            OP_FS_NAMESPACE_DROP => Some(MountRequest::DropNamespace { namespace: read_u32(data, 4)? }),
+            _ => None,
+        }
+    }
+}
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    orion_sys::clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn idle_ns(duration: u64) {
+    let _ = orion_sys::nanosleep(duration);
+}
+
+/// Ready once something is mounted on the root
+fn root_mounted(server: &FileSystemServer) -> Result<(), String> {
+    if server.vfs.root_mounted() {
+        Ok(())
+    } else {
+        Err(String::from("nothing mounted on /"))
+    }
+}
 
 struct FileSystemServer {
-    vfs: VirtualFileSystem,
+    vfs: Arc<VirtualFileSystem>,
+    rings: RingTable,
+    /// Character devices registered in /dev
+    devices: DeviceTable,
+    /// Keys of encrypted directories, shared with their mounts
+    keys: Arc<Mutex<KeyTable>>,
+    /// Devices held for mounts
+    holds: Arc<DeviceHolds>,
+    pool: WorkerPool<IpcMessage>,
     ipc_channel: IpcChannel,
     capabilities: Capability,
+    /// Checks answered to the health server
+    health: HealthChecks<FileSystemServer>,
 }
 
 impl FileSystemServer {
-    fn new() -> Self {
-        let mut server = Self {
-            vfs: VirtualFileSystem::new(),
+    fn new(workers: usize) -> Self {
+        let server = Self {
+            vfs: Arc::new(VirtualFileSystem::new()),
+            rings: RingTable::new(),
+            devices: DeviceTable::new(),
+            keys: Arc::new(Mutex::new(KeyTable::new())),
+            holds: Arc::new(DeviceHolds::new(holds::io_hold, holds::io_release)),
+            pool: WorkerPool::new(workers),
             ipc_channel: IpcChannel::new(),
             capabilities: Capability::new(),
+            health: HealthChecks::new().readiness("root", root_mounted),
         };
 
         // Initialize with a RAM filesystem at root
@@ -49,7 +229,7 @@ impl FileSystemServer {
         server
     }
 
-    fn initialize_root_fs(&mut self) {
+    fn initialize_root_fs(&self) {
         // Mount a RAM filesystem at root
         if let Err(_e) = self.vfs.mount("/", FileSystemType::RamFS, "ram0", "defaults") {
             // TODO: Log error
@@ -67,20 +247,282 @@ impl FileSystemServer {
         if let Err(_e) = self.vfs.create("/home", FileType::Directory) {
             // TODO: Log error
         }
+
+        // Device nodes are registered by their drivers, /dev/random and
+        // /dev/urandom by the entropy server (see devfs.rs)
+        if let Err(_e) = self.vfs.create("/dev", FileType::Directory) {
+            // TODO: Log error
+        }
     }
 
-    fn run(&mut self) {
-        // TODO: Implement main server loop
-        // - Handle IPC messages
-        // - Process file system requests
-        // - Manage mount points
-        // - Handle errors and recovery
+    /// Receive requests and hand them to the worker tasks
+    fn run(self: Rc<Self>) {
+        orion_async::set_clock(monotonic_ns);
+        orion_async::set_idle(idle_ns);
+        orion_async::block_on(async {
+            for _ in 0..self.pool.workers() {
+                let server = self.clone();
+                orion_async::spawn(async move {
+                    while let Some(message) = server.pool.next().await {
+                        server.handle_message(message).await;
+                        server.pool.finish();
+                    }
+                });
+            }
+
+            loop {
+                match self.ipc_channel.receive() {
+                    Some(message) => self.pool.dispatch(message).await,
+                    // Block on the channel only once every request is answered
+                    None if self.pool.in_flight() == 0 => self.ipc_channel.wait(),
+                    None => orion_async::sleep(RECEIVE_POLL_NS).await,
+                }
+            }
+        });
+    }
+
+    /// Scope of absolute paths for `sender`: its mount namespace, set up
+    /// from the parent's table the first time. None when the namespace is
+    /// unknown and cannot be derived, which the sender must not take for
+    /// the initial one
+    fn scope_of(&self, sender: u64) -> Option<PathScope> {
+        let info = orion_sys::ns_info(sender).ok()?;
+        if let Some(scope) = self.vfs.namespace_scope(info.mount) {
+            return Some(scope);
+        }
+        // Two requests may race to create it; either copy does
+        let _ = self.vfs.create_namespace(info.mount, info.mount_parent, "/");
+        self.vfs.namespace_scope(info.mount)
+    }
+
+    async fn handle_message(&self, message: IpcMessage) {
+        // Not answered; a stranger cannot pass for the I/O server
+        if let Some((device, hold)) = holds::decode_revoked(&message.data) {
+            if orion_ipc::lookup("io") == Some(message.sender) {
+                self.holds.revoke(device, hold);
+                return;
+            }
+        }
+
+        if let Some(response) = self.health.serve(self, &message.data) {
+            self.ipc_channel.send(message.sender, &response);
+            return;
+        }
+
+        // Direct file requests (see files.rs); opening needs the rights it
+        // asks for, and linking or renaming the right to write, then what
+        // the MAC policy lets the sender do with the paths
+        if let Some(request) = FileRequest::decode(&message.data) {
+            if request.names_path() {
+                let rights = if request.writes() { CAP_READ | CAP_WRITE } else { CAP_READ };
+                if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+                    self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+                    return;
+                }
+                for (path, perms) in request.mac_accesses() {
+                    if let Err(status) = orion_sys::mac_check(message.sender, CLASS_FILE, &path, perms) {
+                        self.ipc_channel.send(message.sender, &reply(status, &[]));
+                        return;
+                    }
+                }
+            }
+            let Some(scope) = self.scope_of(message.sender) else {
+                self.ipc_channel.send(message.sender, &reply(STATUS_ENOENT, &[]));
+                return;
+            };
+            let response = match files::serve(&self.pool, &self.vfs, scope, request).await {
+                Ok(payload) => reply(STATUS_OK, &payload),
+                Err(status) => reply(status, &[]),
+            };
+            self.ipc_channel.send(message.sender, &response);
+            return;
+        }
+
+        if let Some(request) = MountRequest::decode(&message.data) {
+            let status = if !self.capabilities.check_rights(message.capability, CAP_ADMIN, message.sender) {
+                STATUS_EPERM
+            } else {
+                match self.scope_of(message.sender) {
+                    Some(scope) => self.serve_mount(scope.namespace(), request).await,
+                    None => STATUS_ENOENT,
+                }
+            };
+            self.ipc_channel.send(message.sender, &reply(status, &[]));
+            return;
+        }
+
+        // Registering a device is for drivers, finding one for everyone
+        if let Some(request) = DevfsRequest::decode(&message.data) {
+            let rights = if matches!(request, DevfsRequest::Lookup { .. }) { CAP_READ } else { CAP_ADMIN };
+            let response = if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+                reply(STATUS_EPERM, &[])
+            } else {
+                match self.devices.serve(&self.vfs, message.sender, request) {
+                    Ok(payload) => reply(STATUS_OK, &payload),
+                    Err(status) => reply(status, &[]),
+                }
+            };
+            self.ipc_channel.send(message.sender, &response);
+            return;
+        }
+
+        if let Some(request) = KeyRequest::decode(&message.data) {
+            let response = if !self.capabilities.check_rights(message.capability, CAP_READ, message.sender) {
+                reply(STATUS_EPERM, &[])
+            } else {
+                let admin = self.capabilities.check_rights(message.capability, CAP_ADMIN, message.sender);
+                match crypt::serve(&self.keys, message.sender, admin, request) {
+                    Ok(payload) => reply(STATUS_OK, &payload),
+                    Err(status) => reply(status, &[]),
+                }
+            };
+            self.ipc_channel.send(message.sender, &response);
+            return;
+        }
+
+        // TODO: Process the remaining file system requests
+        let request = match RingRequest::decode(&message.data) {
+            Some(request) => request,
+            None if message.data.get(..4) == Some(&OP_FS_WORKER_STATS.to_le_bytes()[..]) => {
+                let mut payload = Vec::new();
+                payload.extend_from_slice(&(self.pool.workers() as u32).to_le_bytes());
+                payload.extend_from_slice(&(self.pool.in_flight() as u32).to_le_bytes());
+                payload.extend_from_slice(&self.pool.statistics().encode());
+                self.ipc_channel.send(message.sender, &reply(STATUS_OK, &payload));
+                return;
+            }
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let sender = message.sender;
+        let mut payload = Vec::new();
+        let status = match request {
+            // The ring region travels as the shared memory capability of the message
+            RingRequest::Register => match orion_sys::shm_attach(message.capability, 0, 0) {
+                Ok((mapping, size)) => match self.rings.register(sender, mapping, size) {
+                    Ok(ring) => {
+                        payload.extend_from_slice(&ring.to_le_bytes());
+                        STATUS_OK
+                    }
+                    Err(status) => {
+                        let _ = orion_sys::shm_detach(mapping);
+                        status
+                    }
+                },
+                Err(_) => STATUS_EPERM,
+            },
+            RingRequest::Enter { ring, to_submit } => {
+                match self.rings.enter(&self.pool, &self.vfs, sender, ring, to_submit).await {
+                    Ok(consumed) => {
+                        payload.extend_from_slice(&consumed.to_le_bytes());
+                        STATUS_OK
+                    }
+                    Err(status) => status,
+                }
+            }
+            RingRequest::Unregister { ring } => match self.rings.unregister(&self.pool, sender, ring).await {
+                Ok(mapping) => {
+                    let _ = orion_sys::shm_detach(mapping);
+                    STATUS_OK
+                }
+                Err(status) => status,
+            },
+        };
+
+        self.ipc_channel.send(sender, &reply(status, &payload));
+    }
+
+    /// Mount or unmount in `namespace`, the sender's, or manage the mount
+    /// namespaces of its children; connecting to a server blocks, so
+    /// mounts run on spawn_blocking
+    async fn serve_mount(&self, namespace: u32, request: MountRequest) -> i32 {
+        let vfs = self.vfs.clone();
+        let result = match request {
+            MountRequest::Mount { fs_type: MOUNT_ENCRYPTED, path, source, options, device } => {
+                let Ok(identifier) = crypt::parse_options(&options) else {
+                    return STATUS_EINVAL;
+                };
+                let Some(scope) = vfs.namespace_scope(namespace) else {
+                    return STATUS_ENOENT;
+                };
+                let keys = self.keys.clone();
+                let holds = self.holds.clone();
+                orion_async::spawn_blocking(move || {
+                    let (lower, base) = vfs.backend_at(scope, &source).map_err(|_| STATUS_ENOENT)?;
+                    let mount = match identifier {
+                        Some(identifier) => EncryptedMount::setup(lower, &base, identifier, keys, crypt::entropy_nonce),
+                        None => EncryptedMount::attach(lower, &base, keys, crypt::entropy_nonce),
+                    };
+                    let mount = mount.map_err(|error| match error.as_str() {
+                        ENOENT => STATUS_ENOENT,
+                        ENOKEY => STATUS_ENOKEY,
+                        ENOTEMPTY => STATUS_ENOTEMPTY,
+                        EINVAL => STATUS_EINVAL,
+                        _ => STATUS_EIO,
+                    })?;
+                    let mut mount: Arc<dyn MountedFileSystem> = Arc::new(mount);
+                    if device != 0 {
+                        mount = holds::held(&holds, mount, device, &path)?;
+                    }
+                    let mounted =
+                        vfs.mount_in(namespace, &path, FileSystemType::Encrypted, &source, &options, mount.clone());
+                    mounted.map_err(|_| {
+                        mount.unmount();
+                        STATUS_EBUSY
+                    })
+                })
+                .await
+            }
+            MountRequest::Mount { fs_type, path, source, options, device } => {
+                if fs_type != MOUNT_NFS || nfs::parse_source(&source).is_none() {
+                    return STATUS_EINVAL;
+                }
+                // A new verifier every time the server starts, so the NFS
+                // server drops the state of the previous instance
+                let verifier = monotonic_ns().to_le_bytes();
+                let holds = self.holds.clone();
+                orion_async::spawn_blocking(move || {
+                    let mut mount: Arc<dyn MountedFileSystem> =
+                        Arc::new(NfsMount::connect(&source, &options, verifier).map_err(|_| STATUS_EIO)?);
+                    if device != 0 {
+                        mount = holds::held(&holds, mount, device, &path)?;
+                    }
+                    vfs.mount_in(namespace, &path, FileSystemType::NFS, &source, &options, mount.clone()).map_err(|_| {
+                        mount.unmount();
+                        STATUS_EBUSY
+                    })
+                })
+                .await
+            }
+            MountRequest::Unmount { path } => {
+                orion_async::spawn_blocking(move || vfs.unmount_in(namespace, &path).map_err(|_| STATUS_EBUSY)).await
+            }
+            // A namespace cannot replace or drop its own table, nor the
+            // initial one
+            MountRequest::Namespace { namespace: child, .. } | MountRequest::DropNamespace { namespace: child }
+                if child == namespace || child == INITIAL_NAMESPACE =>
+            {
+                Err(STATUS_EBUSY)
+            }
+            MountRequest::Namespace { namespace: child, root } => {
+                if vfs.namespace_scope(child).is_some() {
+                    return STATUS_EEXIST;
+                }
+                vfs.create_namespace(child, namespace, &root).map_err(|_| STATUS_ENOENT)
+            }
+            MountRequest::DropNamespace { namespace: child } => vfs.drop_namespace(child).map_err(|_| STATUS_ENOENT),
+        };
+        result.map_or_else(|status| status, |_| STATUS_OK)
     }
 }
 
 fn main() {
-    let mut server = FileSystemServer::new();
-    
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let server = Rc::new(FileSystemServer::new(FS_WORKERS));
+
     // TODO: Initialize IPC and start serving
     server.run();
 }
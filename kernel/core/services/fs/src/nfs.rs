@@ -0,0 +1,340 @@
+/*
+ * Orion Operating System - File System Server NFS Mounts
+ *
+ * Mount backend for NFSv4.1 shares: the client of orion_nfs runs over a
+ * TCP stream of the network server, and every VFS call on the mount is
+ * one COMPOUND to the server, made from spawn_blocking. The client
+ * handles one request at a time (its session has a single slot), so
+ * calls on a mount are serialized by its lock.
+ *
+ * The source of a mount is "a.b.c.d:/export". Options are comma-separated:
+ * port=N (default 2049), uid=N and gid=N for the AUTH_SYS identity
+ * (default 0, usually squashed by the server) and clientid=NAME, which
+ * must differ between machines mounting the same server (default the
+ * source).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+use orion_nfs::ops::{
+    NFS4ERR_ACCESS, NFS4ERR_EXIST, NFS4ERR_ISDIR, NFS4ERR_NOENT, NFS4ERR_NOSPC, NFS4ERR_NOTDIR, NFS4ERR_PERM,
+    NFS4ERR_ROFS, SHARE_ACCESS_BOTH, SHARE_ACCESS_READ, SHARE_ACCESS_WRITE,
+};
+use orion_nfs::{Auth, Client, NfsError, RpcTransport, NFS_PORT};
+use spin::Mutex;
+
+use crate::vfs::{DirEntry, FileAttributes, FilePermissions, FileType, MountedFileSystem, OpenFlags};
+
+// Socket requests of the network server (see services/net/socket_ipc.h)
+const SOCKET_OP_SEND: u32 = 4;
+const SOCKET_OP_RECV: u32 = 5;
+const SOCKET_OP_CLOSE: u32 = 6;
+const SOCKET_OP_CONNECT: u32 = 12;
+const SOCKET_EIO: i32 = -5;
+const SOCKET_EAGAIN: i32 = -11;
+const SOCKET_MAX_TRANSFER: usize = 8192;
+
+/// Pause before retrying a socket that had nothing to give or take
+const SOCKET_RETRY_NS: u64 = 1_000_000;
+
+/// Retries before a silent server is given up on, about 30 seconds
+const SOCKET_MAX_RETRIES: u32 = 30_000;
+
+/// Mode of files created through the mount
+const CREATE_MODE: u32 = 0o644;
+
+/// Blocking TCP stream of the network server
+struct Socket {
+    channel: IpcChannel,
+    id: u32,
+}
+
+impl Socket {
+    fn call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.channel.call(request).map_err(|_| SOCKET_EIO)?;
+        if response.len() < 4 {
+            return Err(SOCKET_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            0 => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn connect(address: u32, port: u16) -> Result<Self, NfsError> {
+        let mut socket = Self { channel: IpcChannel::connect("net"), id: 0 };
+        let mut request = SOCKET_OP_CONNECT.to_le_bytes().to_vec();
+        request.extend_from_slice(&address.to_le_bytes());
+        request.extend_from_slice(&port.to_le_bytes());
+        let payload = socket.call(&request).map_err(|_| NfsError::Transport)?;
+        let id = payload.get(..4).ok_or(NfsError::Transport)?;
+        socket.id = u32::from_le_bytes([id[0], id[1], id[2], id[3]]);
+        Ok(socket)
+    }
+
+    /// Repeat `attempt` while the socket answers EAGAIN
+    fn retry<R>(&mut self, mut attempt: impl FnMut(&mut Self) -> Result<R, i32>) -> Result<R, NfsError> {
+        for _ in 0..SOCKET_MAX_RETRIES {
+            match attempt(self) {
+                Ok(result) => return Ok(result),
+                Err(SOCKET_EAGAIN) => {
+                    let _ = orion_sys::nanosleep(SOCKET_RETRY_NS);
+                }
+                Err(_) => return Err(NfsError::Transport),
+            }
+        }
+        Err(NfsError::Transport)
+    }
+}
+
+impl RpcTransport for Socket {
+    fn send(&mut self, data: &[u8]) -> Result<(), NfsError> {
+        let mut sent = 0;
+        while sent < data.len() {
+            let end = data.len().min(sent + SOCKET_MAX_TRANSFER);
+            let mut request = SOCKET_OP_SEND.to_le_bytes().to_vec();
+            request.extend_from_slice(&self.id.to_le_bytes());
+            request.extend_from_slice(&data[sent..end]);
+            let payload = self.retry(|socket| socket.call(&request))?;
+            let accepted = payload.get(..4).ok_or(NfsError::Transport)?;
+            match u32::from_le_bytes([accepted[0], accepted[1], accepted[2], accepted[3]]) {
+                0 => {
+                    let _ = orion_sys::nanosleep(SOCKET_RETRY_NS);
+                }
+                accepted => sent += accepted as usize,
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, NfsError> {
+        let mut request = SOCKET_OP_RECV.to_le_bytes().to_vec();
+        request.extend_from_slice(&self.id.to_le_bytes());
+        request.extend_from_slice(&(buffer.len().min(SOCKET_MAX_TRANSFER) as u32).to_le_bytes());
+        let data = self.retry(|socket| socket.call(&request))?;
+        if data.len() > buffer.len() {
+            return Err(NfsError::Transport);
+        }
+        buffer[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let mut request = SOCKET_OP_CLOSE.to_le_bytes().to_vec();
+        request.extend_from_slice(&self.id.to_le_bytes());
+        let _ = self.channel.call(&request);
+    }
+}
+
+/// Split "a.b.c.d:/export" into the server address and the export
+pub fn parse_source(source: &str) -> Option<(u32, &str)> {
+    let (host, export) = source.split_once(':')?;
+    if !export.starts_with('/') {
+        return None;
+    }
+    let mut address = 0u32;
+    let mut octets = 0;
+    for octet in host.split('.') {
+        address = address << 8 | octet.parse::<u8>().ok()? as u32;
+        octets += 1;
+    }
+    (octets == 4).then_some((address, export))
+}
+
+fn option<'a>(options: &'a str, name: &str) -> Option<&'a str> {
+    options.split(',').find_map(|option| option.strip_prefix(name)?.strip_prefix('='))
+}
+
+fn error(error: NfsError) -> String {
+    match error {
+        NfsError::Status(NFS4ERR_NOENT) => "No such file or directory",
+        NfsError::Status(NFS4ERR_PERM | NFS4ERR_ACCESS) => "Permission denied",
+        NfsError::Status(NFS4ERR_EXIST) => "File exists",
+        NfsError::Status(NFS4ERR_NOTDIR) => "Not a directory",
+        NfsError::Status(NFS4ERR_ISDIR) => "Is a directory",
+        NfsError::Status(NFS4ERR_NOSPC) => "No space left on device",
+        NfsError::Status(NFS4ERR_ROFS) => "Read-only file system",
+        NfsError::WritesLost => "Unstable writes lost by the server",
+        NfsError::Transport => "Server unreachable",
+        _ => "Input/output error",
+    }
+    .to_string()
+}
+
+fn attributes(attributes: &orion_nfs::Attributes) -> FileAttributes {
+    let file_type = match attributes.file_type {
+        orion_nfs::FileType::Regular => FileType::Regular,
+        orion_nfs::FileType::Directory => FileType::Directory,
+        orion_nfs::FileType::Symlink => FileType::SymbolicLink,
+        orion_nfs::FileType::Other => FileType::Regular,
+    };
+    let mut converted = FileAttributes::new(attributes.file_id, file_type);
+    converted.size = attributes.size;
+    converted.blocks = attributes.size.div_ceil(512);
+    converted.permissions = FilePermissions::from_mode(attributes.mode);
+    converted.modification_time = attributes.modified.max(0) as u64;
+    converted
+}
+
+struct Share {
+    client: Client<Socket>,
+    files: BTreeMap<u64, orion_nfs::OpenFile>,
+    next_file: u64,
+}
+
+/// An NFSv4.1 export mounted in the VFS
+pub struct NfsMount {
+    share: Mutex<Option<Share>>,
+}
+
+impl NfsMount {
+    /// Connect to the server of `source` and establish a session;
+    /// `verifier` must change every time the file system server starts
+    pub fn connect(source: &str, options: &str, verifier: [u8; 8]) -> Result<Self, String> {
+        let (address, export) = parse_source(source).ok_or_else(|| "Invalid source".to_string())?;
+        let number = |name: &str, default: u32| match option(options, name) {
+            Some(value) => value.parse::<u32>().map_err(|_| "Invalid options".to_string()),
+            None => Ok(default),
+        };
+        let port = u16::try_from(number("port", NFS_PORT as u32)?).map_err(|_| "Invalid options".to_string())?;
+        let auth = Auth::Sys {
+            machine: String::from("orion"),
+            uid: number("uid", 0)?,
+            gid: number("gid", 0)?,
+            groups: Vec::new(),
+        };
+        let owner = option(options, "clientid").unwrap_or(source);
+
+        let socket = Socket::connect(address, port).map_err(error)?;
+        let client = Client::connect(socket, auth, owner.as_bytes(), verifier, export).map_err(error)?;
+        Ok(Self { share: Mutex::new(Some(Share { client, files: BTreeMap::new(), next_file: 1 })) })
+    }
+
+    fn with<R>(&self, operation: impl FnOnce(&mut Share) -> Result<R, NfsError>) -> Result<R, String> {
+        match self.share.lock().as_mut() {
+            Some(share) => operation(share).map_err(error),
+            None => Err("Not mounted".to_string()),
+        }
+    }
+}
+
+impl MountedFileSystem for NfsMount {
+    fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String> {
+        let access = match (flags.is_read(), flags.is_write()) {
+            (true, true) => SHARE_ACCESS_BOTH,
+            (false, true) => SHARE_ACCESS_WRITE,
+            _ => SHARE_ACCESS_READ,
+        };
+        let create = flags.is_create().then_some(CREATE_MODE);
+        self.with(|share| {
+            let file = share.client.open(path, access, create)?;
+            let id = share.next_file;
+            share.next_file += 1;
+            share.files.insert(id, file);
+            Ok(id)
+        })
+    }
+
+    fn read_at(&self, file: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+        self.with(|share| {
+            let open = share.files.get(&file).ok_or(NfsError::Malformed)?;
+            let mut read = 0;
+            while read < buffer.len() {
+                let length = (buffer.len() - read).min(u32::MAX as usize) as u32;
+                let (data, eof) = share.client.read(open, offset + read as u64, length)?;
+                if data.len() > buffer.len() - read {
+                    return Err(NfsError::Malformed);
+                }
+                buffer[read..read + data.len()].copy_from_slice(&data);
+                read += data.len();
+                if eof || data.is_empty() {
+                    break;
+                }
+            }
+            Ok(read)
+        })
+    }
+
+    fn write_at(&self, file: u64, offset: u64, buffer: &[u8]) -> Result<usize, String> {
+        self.with(|share| {
+            let open = share.files.get_mut(&file).ok_or(NfsError::Malformed)?;
+            let mut written = 0;
+            while written < buffer.len() {
+                match share.client.write(open, offset + written as u64, &buffer[written..])? {
+                    0 => break,
+                    count => written += count,
+                }
+            }
+            Ok(written)
+        })
+    }
+
+    fn sync(&self, file: u64) -> Result<(), String> {
+        self.with(|share| {
+            let open = share.files.get_mut(&file).ok_or(NfsError::Malformed)?;
+            share.client.commit(open)
+        })
+    }
+
+    fn close(&self, file: u64) -> Result<(), String> {
+        self.with(|share| match share.files.remove(&file) {
+            Some(open) => share.client.close(open),
+            None => Err(NfsError::Malformed),
+        })
+    }
+
+    fn get_attributes(&self, path: &str) -> Result<FileAttributes, String> {
+        self.with(|share| share.client.getattr(path)).map(|found| attributes(&found))
+    }
+
+    fn read_directory(&self, path: &str) -> Result<Vec<DirEntry>, String> {
+        let entries = self.with(|share| share.client.read_dir(path))?;
+        Ok(entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let converted = attributes(&entry.attributes);
+                DirEntry {
+                    name_len: entry.name.len().min(u8::MAX as usize) as u8,
+                    name: entry.name,
+                    inode: converted.inode,
+                    file_type: converted.file_type,
+                    offset: index as u64,
+                }
+            })
+            .collect())
+    }
+
+    fn unmount(&self) {
+        // Open files were closed before the VFS let the mount go
+        if let Some(share) = self.share.lock().take() {
+            let _ = share.client.disconnect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_sources_and_options() {
+        assert_eq!(parse_source("10.0.0.5:/srv/share"), Some((0x0A00_0005, "/srv/share")));
+        assert_eq!(parse_source("10.0.0.5:srv"), None);
+        assert_eq!(parse_source("10.0.5:/srv"), None);
+        assert_eq!(parse_source("nas:/srv"), None);
+        assert_eq!(option("port=2050,clientid=ws-12", "clientid"), Some("ws-12"));
+        assert_eq!(option("portmap=1", "port"), None);
+    }
+}
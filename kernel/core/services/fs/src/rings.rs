@@ -0,0 +1,262 @@
+/*
+ * Orion Operating System - File System Server Rings
+ *
+ * Submission/completion rings registered by applications (see
+ * lib/orion_ring). On each doorbell the queued entries run against the
+ * VFS in order, and every completion is posted before the server
+ * answers, so an application only waits on the doorbell itself. A
+ * doorbell holds its ring, and each entry the lock of its open file,
+ * while other rings are served by the other workers.
+ * Socket entries belong to the network server and are refused here.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::vec;
+
+use orion_async::{spawn_blocking, AsyncMutex};
+use orion_ring::protocol::MAX_RINGS_PER_PROCESS;
+use orion_ring::ring::{
+    Cqe, RingError, ServerRing, Sqe, OFFSET_CURRENT, OP_CLOSE, OP_FSYNC, OP_NOP, OP_READ, OP_WRITE,
+};
+use spin::Mutex;
+
+use crate::vfs::VirtualFileSystem;
+use crate::workers::WorkerPool;
+
+/// Rings across all processes
+const MAX_RINGS: usize = 64;
+
+// Completion and reply status codes
+const STATUS_ENOENT: i32 = -2;
+const STATUS_EBADF: i32 = -9;
+const STATUS_ENOSPC: i32 = -28;
+const STATUS_EOPNOTSUPP: i32 = -95;
+
+struct RegisteredRing {
+    ring: ServerRing,
+    // Address the shared memory object is mapped at
+    mapping: u64,
+    // Unregistered while a doorbell waited for the ring
+    closed: bool,
+}
+
+struct RingSlot {
+    owner: u64,
+    // Held by the doorbell consuming the ring's entries; rings never leave
+    // the executor thread
+    ring: Rc<AsyncMutex<RegisteredRing>>,
+}
+
+struct RingSlots {
+    rings: BTreeMap<u32, RingSlot>,
+    next_id: u32,
+}
+
+pub struct RingTable {
+    slots: Mutex<RingSlots>,
+}
+
+impl RingTable {
+    pub fn new() -> Self {
+        Self { slots: Mutex::new(RingSlots { rings: BTreeMap::new(), next_id: 1 }) }
+    }
+
+    /// Take over a mapped ring region for `owner`
+    pub fn register(&self, owner: u64, mapping: u64, size: usize) -> Result<u32, i32> {
+        let mut slots = self.slots.lock();
+        let owned = slots.rings.values().filter(|slot| slot.owner == owner).count();
+        if slots.rings.len() >= MAX_RINGS || owned >= MAX_RINGS_PER_PROCESS {
+            return Err(STATUS_ENOSPC);
+        }
+        // The mapping stays in place until the ring is unregistered
+        let ring = unsafe { ServerRing::attach(mapping as *mut u8, size) }.map_err(|error| error.status())?;
+
+        let mut id = slots.next_id;
+        while id == 0 || slots.rings.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+        slots.next_id = id.wrapping_add(1);
+        let ring = Rc::new(AsyncMutex::new(RegisteredRing { ring, mapping, closed: false }));
+        slots.rings.insert(id, RingSlot { owner, ring });
+        Ok(id)
+    }
+
+    fn slot(&self, owner: u64, ring: u32) -> Result<Rc<AsyncMutex<RegisteredRing>>, i32> {
+        match self.slots.lock().rings.get(&ring) {
+            Some(slot) if slot.owner == owner => Ok(slot.ring.clone()),
+            _ => Err(STATUS_ENOENT),
+        }
+    }
+
+    /// Forget a ring, returning the mapping to detach. Waits for a doorbell
+    /// in progress, which still uses the mapping
+    pub async fn unregister<T>(&self, pool: &WorkerPool<T>, owner: u64, ring: u32) -> Result<u64, i32> {
+        let slot = self.slot(owner, ring)?;
+        let mut registered = pool.order_ring(&slot).await;
+        if registered.closed {
+            return Err(STATUS_ENOENT);
+        }
+        registered.closed = true;
+        self.slots.lock().rings.remove(&ring);
+        Ok(registered.mapping)
+    }
+
+    /// Doorbell: run up to `to_submit` queued entries (0 for all), in order
+    pub async fn enter<T>(
+        &self,
+        pool: &WorkerPool<T>,
+        vfs: &Arc<VirtualFileSystem>,
+        owner: u64,
+        ring: u32,
+        to_submit: u32,
+    ) -> Result<u32, i32> {
+        let slot = self.slot(owner, ring)?;
+        let mut registered = pool.order_ring(&slot).await;
+        if registered.closed {
+            return Err(STATUS_ENOENT);
+        }
+
+        let ring = &mut registered.ring;
+        let mut consumed = 0;
+        while to_submit == 0 || consumed < to_submit {
+            let sqe = match ring.next_submission().map_err(|error| error.status())? {
+                Some(sqe) => sqe,
+                None => break,
+            };
+            let result = execute(pool, vfs, ring, &sqe).await;
+            ring.complete(&Cqe { user_data: sqe.user_data, result, flags: 0 }).map_err(|error| error.status())?;
+            consumed += 1;
+        }
+        Ok(consumed)
+    }
+}
+
+/// Run one entry holding its file's ordering lock, which also keeps
+/// OFFSET_CURRENT reads and writes from racing on the file position
+async fn execute<T>(pool: &WorkerPool<T>, vfs: &Arc<VirtualFileSystem>, ring: &ServerRing, sqe: &Sqe) -> i32 {
+    let handle = sqe.handle as u64;
+    let length = sqe.length as usize;
+    let offset = sqe.offset;
+    // Bound the bounce buffer by the data area before allocating it
+    if matches!(sqe.opcode, OP_READ | OP_WRITE) && length > ring.data_size() {
+        return RingError::OutOfBounds.status();
+    }
+    match sqe.opcode {
+        OP_NOP => return 0,
+        OP_READ | OP_WRITE | OP_FSYNC | OP_CLOSE => {}
+        _ => return STATUS_EOPNOTSUPP,
+    }
+
+    let lock = pool.file_lock(handle);
+    let _order = pool.order_file(&lock).await;
+    let vfs = vfs.clone();
+    match sqe.opcode {
+        OP_READ => {
+            let read = spawn_blocking(move || {
+                let mut data = vec![0u8; length];
+                let read = if offset == OFFSET_CURRENT {
+                    vfs.read(handle, &mut data)
+                } else {
+                    vfs.read_at(handle, offset, &mut data)
+                };
+                read.map(|count| {
+                    data.truncate(count);
+                    data
+                })
+            })
+            .await;
+            match read {
+                Ok(data) => match ring.write_data(sqe.buffer, &data) {
+                    Ok(()) => data.len() as i32,
+                    Err(error) => error.status(),
+                },
+                Err(_) => STATUS_EBADF,
+            }
+        }
+        OP_WRITE => {
+            let mut data = vec![0u8; length];
+            if let Err(error) = ring.read_data(sqe.buffer, &mut data) {
+                return error.status();
+            }
+            let written = spawn_blocking(move || {
+                if offset == OFFSET_CURRENT {
+                    vfs.write(handle, &data)
+                } else {
+                    vfs.write_at(handle, offset, &data)
+                }
+            })
+            .await;
+            written.map_or(STATUS_EBADF, |count| count as i32)
+        }
+        OP_FSYNC => spawn_blocking(move || vfs.sync(handle)).await.map_or(STATUS_EBADF, |_| 0),
+        _ => match spawn_blocking(move || vfs.close(handle)).await {
+            Ok(()) => {
+                pool.forget_file(handle);
+                0
+            }
+            Err(_) => STATUS_EBADF,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::{FileSystemType, FileType, OpenFlags};
+    use alloc::vec::Vec;
+    use orion_async::block_on;
+    use orion_ring::ring::{ApplicationRing, RingLayout, OP_SEND};
+
+    #[test]
+    fn test_runs_entries_against_the_vfs() {
+        let vfs = Arc::new(VirtualFileSystem::new());
+        vfs.mount("/", FileSystemType::RamFS, "ram0", "defaults").unwrap();
+        vfs.create("/log", FileType::Regular).unwrap();
+        let file = vfs.open("/log", OpenFlags::from_flags(0o2)).unwrap() as u32;
+
+        let layout = RingLayout::new(4, 4).unwrap();
+        let mut memory = vec![0u64; layout.region_size(64).div_ceil(8)];
+        let size = memory.len() * 8;
+        let base = memory.as_mut_ptr() as *mut u8;
+        let mut application = unsafe { ApplicationRing::init(base, size, layout) }.unwrap();
+
+        let pool = WorkerPool::<()>::new(1);
+        let rings = RingTable::new();
+        let ring = rings.register(7, base as u64, size).unwrap();
+
+        let entries = [
+            Sqe { opcode: OP_WRITE, handle: file, offset: 0, length: 16, user_data: 1, ..Sqe::default() },
+            Sqe { opcode: OP_SEND, handle: file, user_data: 2, ..Sqe::default() },
+            Sqe { opcode: OP_FSYNC, handle: 99, user_data: 3, ..Sqe::default() },
+            Sqe { opcode: OP_CLOSE, handle: file, user_data: 4, ..Sqe::default() },
+        ];
+        for sqe in entries.iter() {
+            application.submit(sqe).unwrap();
+        }
+
+        assert_eq!(block_on(rings.enter(&pool, &vfs, 8, ring, 0)), Err(STATUS_ENOENT));
+        assert_eq!(block_on(rings.enter(&pool, &vfs, 7, ring, 0)), Ok(4));
+        let results: Vec<(u64, i32)> =
+            core::iter::from_fn(|| application.next_completion()).map(|cqe| (cqe.user_data, cqe.result)).collect();
+        assert_eq!(results, [(1, 16), (2, STATUS_EOPNOTSUPP), (3, STATUS_EBADF), (4, 0)]);
+
+        // Buffers beyond the data area are refused before touching the file
+        application
+            .submit(&Sqe { opcode: OP_READ, handle: file, length: 4096, user_data: 5, ..Sqe::default() })
+            .unwrap();
+        assert_eq!(block_on(rings.enter(&pool, &vfs, 7, ring, 0)), Ok(1));
+        assert_eq!(application.next_completion().unwrap().result, RingError::OutOfBounds.status());
+
+        assert_eq!(block_on(rings.unregister(&pool, 8, ring)), Err(STATUS_ENOENT));
+        assert_eq!(block_on(rings.unregister(&pool, 7, ring)), Ok(base as u64));
+        assert_eq!(block_on(rings.enter(&pool, &vfs, 7, ring, 0)), Err(STATUS_ENOENT));
+    }
+}
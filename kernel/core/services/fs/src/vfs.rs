@@ -17,9 +17,11 @@ use core::sync::atomic::{AtomicU64, AtomicU32, Ordering};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use alloc::sync::Arc;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
 use spin::RwLock;
 
+use crate::dcache::{DcacheStatistics, Dentry, DentryCache};
+
 // ========================================
 // HIGH-PERFORMANCE VFS CONSTANTS
 // ========================================
@@ -30,6 +32,35 @@ const MAX_OPEN_FILES: usize = 16384;    // Increased from 4096
 const MAX_FILENAME_LEN: usize = 255;    // Same
 const MAX_PATH_LEN: usize = 8192;      // Increased from 4096
 const VFS_CACHE_SIZE: usize = 4096;    // Increased from 1024
+const VFS_NEGATIVE_CACHE_SIZE: usize = VFS_CACHE_SIZE / 4;
+const ROOT_INODE: u64 = 1;
+const MAX_SYMLINK_HOPS: usize = 40;    // ELOOP beyond, as Linux
+const MAX_MOUNT_NAMESPACES: usize = 64; // As the kernel (namespace.h)
+
+/// Mount namespace the server starts with, the kernel's initial one
+pub const INITIAL_NAMESPACE: u32 = 0;
+
+/// Do not follow a symbolic link in the last component
+pub const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+
+const ELOOP: &str = "Too many levels of symbolic links";
+const EXDEV: &str = "Path escapes its scope";
+pub const EBUSY: &str = "Device or resource busy";
+pub const EEXIST: &str = "File exists";
+pub const ENOENT: &str = "No such file or directory";
+pub const ENOTDIR: &str = "Not a directory";
+pub const EISDIR: &str = "Is a directory";
+pub const EINVAL: &str = "Invalid argument";
+pub const ENOTEMPTY: &str = "Directory not empty";
+pub const EBADF: &str = "Invalid file handle";
+/// A directory a caller locked no longer holds the path it named
+pub const ESTALE: &str = "Stale file handle";
+/// The key of an encrypted directory is not in the key table
+pub const ENOKEY: &str = "Required key not available";
+pub const EIO: &str = "Input/output error";
+
+/// rename_at flag: fail rather than replace an existing target
+pub const RENAME_NOREPLACE: u32 = 1 << 0;
 
 // File types (POSIX compatible)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -188,6 +219,55 @@ impl OpenFlags {
     pub fn is_create(&self) -> bool { (self.flags & 0o10) != 0 }
     pub fn is_truncate(&self) -> bool { (self.flags & 0o20) != 0 }
     pub fn is_exclusive(&self) -> bool { (self.flags & 0o40) != 0 }
+    pub fn is_nofollow(&self) -> bool { (self.flags & 0o100) != 0 }
+    pub fn is_tmpfile(&self) -> bool { (self.flags & 0o200) != 0 }
+}
+
+/// Whether an open file has a name in the tree. A file opened with
+/// O_TMPFILE has none until it is linked, and never gets one when it was
+/// opened with O_EXCL as well
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    Named,
+    Anonymous { linkable: bool },
+}
+
+/// File system serving a mount point whose files do not live in the VFS
+/// tree, such as a network share. Paths are relative to the mount point
+/// and start with '/'; `open` returns an identifier of the backend's own
+/// that the other file calls take. Calls block until the backend answers,
+/// so the server only makes them from spawn_blocking.
+pub trait MountedFileSystem: Send + Sync {
+    fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String>;
+    fn read_at(&self, file: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String>;
+    fn write_at(&self, file: u64, offset: u64, buffer: &[u8]) -> Result<usize, String>;
+    fn sync(&self, file: u64) -> Result<(), String>;
+    fn close(&self, file: u64) -> Result<(), String>;
+    fn get_attributes(&self, path: &str) -> Result<FileAttributes, String>;
+    fn read_directory(&self, path: &str) -> Result<Vec<DirEntry>, String>;
+    /// Release the backend once its mount point is gone
+    fn unmount(&self) {}
+    /// Backend this one keeps its files on, which cannot be unmounted
+    /// while this one is mounted
+    fn stacked_on(&self) -> Option<Arc<dyn MountedFileSystem>> {
+        None
+    }
+}
+
+/// Backend serving a path, with the path relative to its mount point
+pub type BackendPath = (Arc<dyn MountedFileSystem>, String);
+
+/// A file opened on a mounted backend
+#[derive(Clone)]
+pub struct RemoteFile {
+    pub backend: Arc<dyn MountedFileSystem>,
+    pub file: u64,
+}
+
+impl core::fmt::Debug for RemoteFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RemoteFile").field("file", &self.file).finish()
+    }
 }
 
 // High-performance open file (removed Clone due to AtomicU64/AtomicU32)
@@ -198,6 +278,8 @@ pub struct OpenFile {
     pub offset: AtomicU64,  // Atomic for thread safety
     pub path: String,
     pub reference_count: AtomicU32,
+    pub remote: Option<RemoteFile>,  // Files of a mounted backend
+    pub linkage: Linkage,
 }
 
 impl OpenFile {
@@ -208,6 +290,8 @@ impl OpenFile {
             offset: AtomicU64::new(0),
             path,
             reference_count: AtomicU32::new(1),
+            remote: None,
+            linkage: Linkage::Named,
         }
     }
 
@@ -251,17 +335,105 @@ pub enum FileSystemType {
     Ext4,
     NFS,
     VirtioFS,
+    Encrypted,
     Unknown,
 }
 
+// Directory entries by (parent inode, name)
+type DirectoryEntries = BTreeMap<(u64, String), (u64, FileType)>;
+
+/// Subtree paths are resolved in. A client holding a directory capability
+/// resolves in the scope of that directory: its paths are relative to it,
+/// and neither "..", an absolute path nor a symbolic link may lead out of
+/// it, like openat2 with RESOLVE_BENEATH.
+///
+/// A scope also names the mount namespace whose mounts absolute paths
+/// reach. A namespace other than the initial one is rooted at a directory
+/// of the tree, which absolute paths start from and ".." stops at, as
+/// after chroot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathScope {
+    root: u64,
+    confined: bool,
+    namespace: u32,
+}
+
+impl PathScope {
+    /// The whole tree, for absolute paths
+    pub const GLOBAL: Self = Self { root: ROOT_INODE, confined: false, namespace: INITIAL_NAMESPACE };
+
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    pub fn is_confined(&self) -> bool {
+        self.confined
+    }
+
+    pub fn namespace(&self) -> u32 {
+        self.namespace
+    }
+}
+
+/// Mounts of a mount namespace: its root in the tree and the backends
+/// mounted in it, by mount point as seen from that root
+struct MountNamespace {
+    root: u64,
+    backends: BTreeMap<String, Arc<dyn MountedFileSystem>>,
+}
+
+// Path resolution in progress
+struct PathWalk {
+    // Components left, next first
+    pending: VecDeque<String>,
+    // Directories walked into, the scope root first
+    directories: Vec<u64>,
+    hops: usize,
+}
+
+impl PathWalk {
+    fn new(scope: PathScope, path: &str) -> Result<Self, String> {
+        match (scope.confined, path.starts_with('/')) {
+            (true, true) => return Err(EXDEV.to_string()),
+            (false, false) => return Err("Relative path".to_string()),
+            _ => {}
+        }
+        let mut walk = Self { pending: VecDeque::new(), directories: alloc::vec![scope.root], hops: 0 };
+        walk.push_front(path)?;
+        Ok(walk)
+    }
+
+    /// Queue the components of `path` ahead of those left
+    fn push_front(&mut self, path: &str) -> Result<(), String> {
+        if path.len() > MAX_PATH_LEN {
+            return Err("Path too long".to_string());
+        }
+        for component in path.rsplit('/') {
+            match component {
+                "" | "." => {}
+                name if name.len() > MAX_FILENAME_LEN => return Err("File name too long".to_string()),
+                name => self.pending.push_front(name.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    fn directory(&self) -> u64 {
+        *self.directories.last().unwrap()
+    }
+}
+
 // High-performance Virtual File System
 pub struct VirtualFileSystem {
     root_mount: Arc<RwLock<Option<MountPoint>>>,
     mounts: Arc<RwLock<BTreeMap<String, MountPoint>>>,
     next_inode: AtomicU64,
-    open_files: Arc<RwLock<BTreeMap<u64, OpenFile>>>,
+    open_files: Arc<RwLock<BTreeMap<u64, OpenFile>>>,  // Locked after the entries
     next_file_handle: AtomicU64,
-    cache: Arc<RwLock<BTreeMap<String, u64>>>,  // Path to inode cache
+    namespaces: Arc<RwLock<BTreeMap<u32, MountNamespace>>>,  // Mount namespaces by id
+    entries: Arc<RwLock<DirectoryEntries>>,  // Locked before the dcache
+    links: Arc<RwLock<BTreeMap<u64, String>>>,  // Symbolic link targets, locked after the entries
+    dcache: Arc<RwLock<DentryCache>>,
     statistics: Arc<RwLock<VfsStatistics>>,
 }
 
@@ -270,10 +442,16 @@ impl VirtualFileSystem {
         Self {
             root_mount: Arc::new(RwLock::new(None)),
             mounts: Arc::new(RwLock::new(BTreeMap::new())),
-            next_inode: AtomicU64::new(1),
+            next_inode: AtomicU64::new(ROOT_INODE + 1),
             open_files: Arc::new(RwLock::new(BTreeMap::new())),
             next_file_handle: AtomicU64::new(1),
-            cache: Arc::new(RwLock::new(BTreeMap::new())),
+            namespaces: Arc::new(RwLock::new(BTreeMap::from([(
+                INITIAL_NAMESPACE,
+                MountNamespace { root: ROOT_INODE, backends: BTreeMap::new() },
+            )]))),
+            entries: Arc::new(RwLock::new(BTreeMap::new())),
+            links: Arc::new(RwLock::new(BTreeMap::new())),
+            dcache: Arc::new(RwLock::new(DentryCache::new(VFS_CACHE_SIZE, VFS_NEGATIVE_CACHE_SIZE))),
             statistics: Arc::new(RwLock::new(VfsStatistics::new())),
         }
     }
@@ -289,6 +467,7 @@ impl VirtualFileSystem {
             let mut mounts = self.mounts.write();
             mounts.insert(path.to_string(), mount_point);
         }
+        self.invalidate_mount(path);
         
         // Update statistics
         let mut stats = self.statistics.write();
@@ -297,8 +476,130 @@ impl VirtualFileSystem {
         Ok(())
     }
 
+    /// Whether a file system is mounted on the root
+    pub fn root_mounted(&self) -> bool {
+        self.root_mount.read().is_some()
+    }
+
+    /// Mount a file system served by `backend` rather than the VFS tree.
+    /// Absolute paths under `path` reach the backend; scoped resolution
+    /// stays in the tree and never crosses into it
+    pub fn mount_with(
+        &self,
+        path: &str,
+        fs_type: FileSystemType,
+        device: &str,
+        options: &str,
+        backend: Arc<dyn MountedFileSystem>,
+    ) -> Result<(), String> {
+        self.mount_in(INITIAL_NAMESPACE, path, fs_type, device, options, backend)
+    }
+
+    /// Mount `backend` in a mount namespace, at `path` as seen from its
+    /// root. Only the initial namespace keeps a mount point record
+    pub fn mount_in(
+        &self,
+        namespace: u32,
+        path: &str,
+        fs_type: FileSystemType,
+        device: &str,
+        options: &str,
+        backend: Arc<dyn MountedFileSystem>,
+    ) -> Result<(), String> {
+        let path = path.trim_end_matches('/');
+        if !path.starts_with('/') || path.split('/').any(|component| component == "..") {
+            return Err("Invalid mount point".to_string());
+        }
+        {
+            let mut namespaces = self.namespaces.write();
+            let backends = &mut namespaces.get_mut(&namespace).ok_or_else(|| ENOENT.to_string())?.backends;
+            if backends.contains_key(path) {
+                return Err(EBUSY.to_string());
+            }
+            if backends.len() >= MAX_MOUNTS {
+                return Err("Too many mounts".to_string());
+            }
+            backends.insert(path.to_string(), backend);
+        }
+        if namespace == INITIAL_NAMESPACE {
+            return self.mount(path, fs_type, device, options);
+        }
+        self.statistics.write().mount_count += 1;
+        Ok(())
+    }
+
+    /// Backend mounted over `path` in `namespace` and the path relative to it
+    fn backend_for(&self, namespace: u32, path: &str) -> Option<BackendPath> {
+        let namespaces = self.namespaces.read();
+        let (mount, backend) = namespaces
+            .get(&namespace)?
+            .backends
+            .iter()
+            .filter(|(mount, _)| {
+                path.strip_prefix(mount.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(mount, _)| mount.len())?;
+        let rest = &path[mount.len()..];
+        Some((backend.clone(), if rest.is_empty() { "/".to_string() } else { rest.to_string() }))
+    }
+
+    /// Backend of an absolute path resolved in `scope`. ".." cannot walk
+    /// out of a backend, as the backend resolves the path on its own
+    fn remote_path(&self, scope: PathScope, path: &str) -> Result<Option<BackendPath>, String> {
+        if scope.confined {
+            return Ok(None);
+        }
+        match self.backend_for(scope.namespace, path) {
+            Some((_, rest)) if rest.split('/').any(|component| component == "..") => Err(EXDEV.to_string()),
+            found => Ok(found),
+        }
+    }
+
+    /// Backend serving the absolute path `path` of `scope`, for a backend
+    /// stacked on a directory of another; ENOENT if the path is in the tree
+    pub fn backend_at(&self, scope: PathScope, path: &str) -> Result<BackendPath, String> {
+        self.remote_path(scope, path)?.ok_or_else(|| ENOENT.to_string())
+    }
+
     /// Unmount a file system (thread-safe)
     pub fn unmount(&self, path: &str) -> Result<(), String> {
+        self.unmount_in(INITIAL_NAMESPACE, path)
+    }
+
+    /// Unmount `path` in a mount namespace. A backend other namespaces
+    /// still hold stays connected, and its open files with it
+    pub fn unmount_in(&self, namespace: u32, path: &str) -> Result<(), String> {
+        let path = if path == "/" { path } else { path.trim_end_matches('/') };
+        let mounted = {
+            let namespaces = self.namespaces.read();
+            let backends = &namespaces.get(&namespace).ok_or_else(|| ENOENT.to_string())?.backends;
+            backends.get(path).cloned()
+        };
+        if let Some(backend) = mounted {
+            if self.stacked_upon(&backend) {
+                return Err(EBUSY.to_string());
+            }
+            let shared = self.held_elsewhere(namespace, &backend);
+            if !shared {
+                let open_files = self.open_files.read();
+                let busy = open_files.values().any(|open_file| {
+                    open_file.remote.as_ref().is_some_and(|remote| Arc::ptr_eq(&remote.backend, &backend))
+                });
+                if busy {
+                    return Err(EBUSY.to_string());
+                }
+            }
+            if let Some(entry) = self.namespaces.write().get_mut(&namespace) {
+                entry.backends.remove(path);
+            }
+            if !shared {
+                backend.unmount();
+            }
+        }
+        if namespace != INITIAL_NAMESPACE {
+            self.statistics.write().unmount_count += 1;
+            return Ok(());
+        }
         if path == "/" {
             let mut root = self.root_mount.write();
             *root = None;
@@ -306,6 +607,7 @@ impl VirtualFileSystem {
             let mut mounts = self.mounts.write();
             mounts.remove(path);
         }
+        self.invalidate_mount(path);
         
         // Update statistics
         let mut stats = self.statistics.write();
@@ -314,13 +616,102 @@ impl VirtualFileSystem {
         Ok(())
     }
 
+    /// Whether a namespace other than `namespace` mounts `backend`
+    fn held_elsewhere(&self, namespace: u32, backend: &Arc<dyn MountedFileSystem>) -> bool {
+        self.namespaces.read().iter().any(|(id, entry)| {
+            *id != namespace && entry.backends.values().any(|other| Arc::ptr_eq(other, backend))
+        })
+    }
+
+    /// Whether a backend mounted in any namespace is stacked on `backend`
+    fn stacked_upon(&self, backend: &Arc<dyn MountedFileSystem>) -> bool {
+        self.namespaces.read().values().any(|entry| {
+            entry.backends.values().any(|other| other.stacked_on().is_some_and(|lower| Arc::ptr_eq(&lower, backend)))
+        })
+    }
+
+    /// Create mount namespace `namespace` as a copy of `parent` rooted at
+    /// the directory `root`, a path of the parent. It starts with the
+    /// parent's mounts lying under that root; mounts and unmounts made in
+    /// either afterwards stay their own
+    pub fn create_namespace(&self, namespace: u32, parent: u32, root: &str) -> Result<(), String> {
+        let scope = self.namespace_scope(parent).ok_or_else(|| ENOENT.to_string())?;
+        let inode = match self.resolve_in(None, scope, root, true)? {
+            (inode, FileType::Directory) => inode,
+            _ => return Err("Not a directory".to_string()),
+        };
+        // The root as a mount point of the parent, to pick its mounts by
+        let prefix = root.trim_end_matches('/');
+
+        let mut namespaces = self.namespaces.write();
+        if namespaces.contains_key(&namespace) {
+            return Err(EEXIST.to_string());
+        }
+        if namespaces.len() >= MAX_MOUNT_NAMESPACES {
+            return Err("Too many namespaces".to_string());
+        }
+        let backends = namespaces[&parent]
+            .backends
+            .iter()
+            .filter_map(|(mount, backend)| match mount.strip_prefix(prefix) {
+                Some(rest) if rest.starts_with('/') => Some((rest.to_string(), backend.clone())),
+                _ => None,
+            })
+            .collect();
+        namespaces.insert(namespace, MountNamespace { root: inode, backends });
+        Ok(())
+    }
+
+    /// Forget a mount namespace once its processes are gone. Backends
+    /// mounted only there are disconnected
+    pub fn drop_namespace(&self, namespace: u32) -> Result<(), String> {
+        if namespace == INITIAL_NAMESPACE {
+            return Err(EBUSY.to_string());
+        }
+        let removed = self.namespaces.write().remove(&namespace).ok_or_else(|| ENOENT.to_string())?;
+        for backend in removed.backends.values() {
+            if !self.held_elsewhere(namespace, backend) {
+                backend.unmount();
+            }
+        }
+        Ok(())
+    }
+
+    /// Scope of absolute paths in a mount namespace, None if it is unknown
+    pub fn namespace_scope(&self, namespace: u32) -> Option<PathScope> {
+        let root = self.namespaces.read().get(&namespace)?.root;
+        Some(PathScope { root, confined: false, namespace })
+    }
+
     /// Open a file (thread-safe, high-performance)
     pub fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String> {
-        let inode = self.lookup_inode(path)?;
+        self.open_at(PathScope::GLOBAL, path, flags)
+    }
+
+    /// Open a file in `scope`. With O_NOFOLLOW a symbolic link as the last
+    /// component fails rather than being followed
+    pub fn open_at(&self, scope: PathScope, path: &str, flags: OpenFlags) -> Result<u64, String> {
+        let open_file = match self.remote_path(scope, path)? {
+            Some((backend, rest)) => {
+                let file = backend.open(&rest, flags)?;
+                let mut open_file = OpenFile::new(0, flags, path.to_string());
+                open_file.remote = Some(RemoteFile { backend, file });
+                open_file
+            }
+            None => {
+                let (inode, file_type) = self.resolve_in(None, scope, path, !flags.is_nofollow())?;
+                if file_type == FileType::SymbolicLink {
+                    return Err(ELOOP.to_string());
+                }
+                OpenFile::new(inode, flags, path.to_string())
+            }
+        };
+        Ok(self.register_open(open_file))
+    }
+
+    fn register_open(&self, open_file: OpenFile) -> u64 {
         let file_handle = self.next_file_handle.fetch_add(1, Ordering::SeqCst);
         
-        let open_file = OpenFile::new(inode, flags, path.to_string());
-        
         {
             let mut open_files = self.open_files.write();
             open_files.insert(file_handle, open_file);
@@ -331,102 +722,327 @@ impl VirtualFileSystem {
         stats.open_count += 1;
         stats.current_open_files += 1;
         
-        Ok(file_handle)
+        file_handle
+    }
+
+    /// Open with O_CREAT in `scope`. The file is created and opened as one
+    /// step, so with O_EXCL the handle is to the file this call created
+    /// even if the name is renamed or replaced right after; without it an
+    /// existing file is opened instead, following a symbolic link to it.
+    /// Returns the handle and whether the file was created
+    pub fn create_open_at(&self, scope: PathScope, path: &str, flags: OpenFlags) -> Result<(u64, bool), String> {
+        if self.remote_path(scope, path)?.is_some() {
+            return self.open_at(scope, path, flags).map(|handle| (handle, false));
+        }
+        match self.insert_entry(scope, path, FileType::Regular, None) {
+            Ok(inode) => Ok((self.register_open(OpenFile::new(inode, flags, path.to_string())), true)),
+            Err(error) if error == EEXIST && !flags.is_exclusive() => {
+                let handle = self.open_at(scope, path, flags)?;
+                Ok((handle, false))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Open an unnamed regular file that will live in the directory at
+    /// `directory`, as O_TMPFILE. It disappears on close unless link_at
+    /// gives it a name first, which O_EXCL rules out
+    pub fn open_tmpfile_at(&self, scope: PathScope, directory: &str, flags: OpenFlags) -> Result<u64, String> {
+        if !flags.is_write() {
+            return Err(EINVAL.to_string());
+        }
+        if self.remote_path(scope, directory)?.is_some() {
+            return Err("Operation not supported".to_string());
+        }
+        match self.resolve_in(None, scope, directory, true)? {
+            (_, FileType::Directory) => {}
+            _ => return Err(ENOTDIR.to_string()),
+        }
+        let inode = self.next_inode.fetch_add(1, Ordering::SeqCst);
+        let mut open_file = OpenFile::new(inode, flags, String::new());
+        open_file.linkage = Linkage::Anonymous { linkable: !flags.is_exclusive() };
+        Ok(self.register_open(open_file))
+    }
+
+    /// Give the unnamed file behind `file_handle` the name `path`, as
+    /// linkat with AT_EMPTY_PATH. Fails with EEXIST if the name is taken
+    /// and ENOENT for a file opened with O_TMPFILE | O_EXCL; a file that
+    /// already has a name is refused
+    pub fn link_at(&self, file_handle: u64, scope: PathScope, path: &str) -> Result<(), String> {
+        {
+            let mut entries = self.entries.write();
+            let mut open_files = self.open_files.write();
+            let open_file = open_files.get_mut(&file_handle).ok_or_else(|| EBADF.to_string())?;
+            match open_file.linkage {
+                Linkage::Anonymous { linkable: true } => {}
+                Linkage::Anonymous { linkable: false } => return Err(ENOENT.to_string()),
+                Linkage::Named => return Err(EINVAL.to_string()),
+            }
+            let key = self.resolve_parent_in(&entries, scope, path)?;
+            if entries.contains_key(&key) {
+                return Err(EEXIST.to_string());
+            }
+            self.dcache.write().invalidate(key.0, &key.1);
+            entries.insert(key, (open_file.inode, FileType::Regular));
+            open_file.linkage = Linkage::Named;
+            open_file.path = path.to_string();
+        }
+
+        let mut stats = self.statistics.write();
+        stats.create_count += 1;
+
+        Ok(())
     }
 
     /// Close a file (thread-safe)
     pub fn close(&self, file_handle: u64) -> Result<(), String> {
-        let mut open_files = self.open_files.write();
-        if let Some(_open_file) = open_files.remove(&file_handle) {
+        let removed = self.open_files.write().remove(&file_handle);
+        if let Some(open_file) = removed {
             // Update statistics
-            let mut stats = self.statistics.write();
-            stats.close_count += 1;
-            stats.current_open_files = stats.current_open_files.saturating_sub(1);
-            
-            Ok(())
+            {
+                let mut stats = self.statistics.write();
+                stats.close_count += 1;
+                stats.current_open_files = stats.current_open_files.saturating_sub(1);
+            }
+
+            // The handle is gone even if the backend fails to close its file
+            match open_file.remote {
+                Some(remote) => remote.backend.close(remote.file),
+                None => Ok(()),
+            }
         } else {
             Err("Invalid file handle".to_string())
         }
     }
 
-    /// Read from a file (thread-safe, optimized)
+    /// Read from a file at its current position (thread-safe, optimized)
     pub fn read(&self, file_handle: u64, buffer: &mut [u8]) -> Result<usize, String> {
-        let open_files = self.open_files.read();
-        if let Some(open_file) = open_files.get(&file_handle) {
+        let offset = self.file_position(file_handle)?;
+        let bytes_read = self.read_at(file_handle, offset, buffer)?;
+        self.advance(file_handle, bytes_read);
+        Ok(bytes_read)
+    }
+
+    /// Read at `offset`, leaving the file position alone
+    pub fn read_at(&self, file_handle: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+        // The backend is called without the open file table locked
+        let bytes_read = match self.remote_file(file_handle)? {
+            Some(remote) => remote.backend.read_at(remote.file, offset, buffer)?,
             // TODO: Implement actual file reading from the mounted file system
-            let bytes_read = 0; // Placeholder
-            
-            open_file.offset.fetch_add(bytes_read as u64, Ordering::Relaxed);
-            
-            // Update statistics
-            let mut stats = self.statistics.write();
-            stats.read_count += 1;
-            stats.bytes_read += bytes_read as u64;
-            
-            Ok(bytes_read)
-        } else {
-            Err("Invalid file handle".to_string())
-        }
+            None => 0, // Placeholder
+        };
+
+        // Update statistics
+        let mut stats = self.statistics.write();
+        stats.read_count += 1;
+        stats.bytes_read += bytes_read as u64;
+
+        Ok(bytes_read)
     }
 
-    /// Write to a file (thread-safe, optimized)
+    /// Write to a file at its current position (thread-safe, optimized)
     pub fn write(&self, file_handle: u64, buffer: &[u8]) -> Result<usize, String> {
-        let open_files = self.open_files.read();
-        if let Some(open_file) = open_files.get(&file_handle) {
+        let offset = self.file_position(file_handle)?;
+        let bytes_written = self.write_at(file_handle, offset, buffer)?;
+        self.advance(file_handle, bytes_written);
+        Ok(bytes_written)
+    }
+
+    /// Write at `offset`, leaving the file position alone
+    pub fn write_at(&self, file_handle: u64, offset: u64, buffer: &[u8]) -> Result<usize, String> {
+        let bytes_written = match self.remote_file(file_handle)? {
+            Some(remote) => remote.backend.write_at(remote.file, offset, buffer)?,
             // TODO: Implement actual file writing to the mounted file system
-            let bytes_written = buffer.len(); // Placeholder
-            
-            open_file.offset.fetch_add(bytes_written as u64, Ordering::Relaxed);
-            
-            // Update statistics
-            let mut stats = self.statistics.write();
-            stats.write_count += 1;
-            stats.bytes_written += bytes_written as u64;
-            
-            Ok(bytes_written)
-        } else {
-            Err("Invalid file handle".to_string())
+            None => buffer.len(), // Placeholder
+        };
+
+        // Update statistics
+        let mut stats = self.statistics.write();
+        stats.write_count += 1;
+        stats.bytes_written += bytes_written as u64;
+
+        Ok(bytes_written)
+    }
+
+    /// Flush the file's dirty data to its device
+    pub fn sync(&self, file_handle: u64) -> Result<(), String> {
+        match self.remote_file(file_handle)? {
+            Some(remote) => remote.backend.sync(remote.file),
+            // TODO: Flush through the mounted file system
+            None => Ok(()),
+        }
+    }
+
+    /// Backend file behind an open file, if it has one
+    fn remote_file(&self, file_handle: u64) -> Result<Option<RemoteFile>, String> {
+        match self.open_files.read().get(&file_handle) {
+            Some(open_file) => Ok(open_file.remote.clone()),
+            None => Err("Invalid file handle".to_string()),
+        }
+    }
+
+    pub fn file_position(&self, file_handle: u64) -> Result<u64, String> {
+        let open_files = self.open_files.read();
+        match open_files.get(&file_handle) {
+            Some(open_file) => Ok(open_file.offset.load(Ordering::Relaxed)),
+            None => Err("Invalid file handle".to_string()),
+        }
+    }
+
+    fn advance(&self, file_handle: u64, bytes: usize) {
+        if let Some(open_file) = self.open_files.read().get(&file_handle) {
+            open_file.offset.fetch_add(bytes as u64, Ordering::Relaxed);
         }
     }
 
     /// Get file attributes (cached for performance)
     pub fn get_attributes(&self, path: &str) -> Result<FileAttributes, String> {
-        let inode = self.lookup_inode(path)?;
+        self.get_attributes_at(PathScope::GLOBAL, path, 0)
+    }
+
+    /// Get file attributes in `scope`; with AT_SYMLINK_NOFOLLOW those of a
+    /// symbolic link itself, as lstat
+    pub fn get_attributes_at(&self, scope: PathScope, path: &str, at_flags: u32) -> Result<FileAttributes, String> {
+        let follow = at_flags & AT_SYMLINK_NOFOLLOW == 0;
+        if let Some((backend, rest)) = self.remote_path(scope, path)? {
+            return backend.get_attributes(&rest);
+        }
+        let (inode, file_type) = self.resolve_in(None, scope, path, follow)?;
         
         // TODO: Get actual attributes from the mounted file system
-        Ok(FileAttributes::new(inode, FileType::Regular))
+        let mut attributes = FileAttributes::new(inode, file_type);
+        if file_type == FileType::SymbolicLink {
+            attributes.size = self.links.read().get(&inode).map_or(0, |target| target.len() as u64);
+        }
+        Ok(attributes)
+    }
+
+    /// Confine further resolution to the directory at `path`, itself
+    /// resolved in `scope`
+    pub fn scope_at(&self, scope: PathScope, path: &str) -> Result<PathScope, String> {
+        match self.resolve_in(None, scope, path, true)? {
+            (inode, FileType::Directory) => Ok(PathScope { root: inode, confined: true, namespace: scope.namespace }),
+            _ => Err("Not a directory".to_string()),
+        }
     }
 
     /// List directory contents (optimized)
-    pub fn read_directory(&self, _path: &str) -> Result<Vec<DirEntry>, String> {
+    pub fn read_directory(&self, path: &str) -> Result<Vec<DirEntry>, String> {
+        if let Some((backend, rest)) = self.remote_path(PathScope::GLOBAL, path)? {
+            return backend.read_directory(&rest);
+        }
         // TODO: Implement directory reading from the mounted file system
         Ok(Vec::new())
     }
 
     /// Create a new file or directory (thread-safe)
-    pub fn create(&mut self, path: &str, file_type: FileType) -> Result<(), String> {
-        let inode = self.next_inode.fetch_add(1, Ordering::SeqCst);
-        
+    pub fn create(&self, path: &str, file_type: FileType) -> Result<(), String> {
+        self.create_at(PathScope::GLOBAL, path, file_type)
+    }
+
+    /// Create a new file or directory in `scope`; symbolic links are made
+    /// with symlink_at
+    pub fn create_at(&self, scope: PathScope, path: &str, file_type: FileType) -> Result<(), String> {
+        if file_type == FileType::SymbolicLink {
+            return Err("Invalid argument".to_string());
+        }
+        self.insert_entry(scope, path, file_type, None).map(|_| ())
+    }
+
+    /// Create a symbolic link at `path` pointing to `target`
+    pub fn symlink(&self, target: &str, path: &str) -> Result<(), String> {
+        self.symlink_at(PathScope::GLOBAL, target, path)
+    }
+
+    /// Create a symbolic link in `scope`. The target is only resolved when
+    /// the link is followed, in the scope of whoever follows it
+    pub fn symlink_at(&self, scope: PathScope, target: &str, path: &str) -> Result<(), String> {
+        if target.is_empty() {
+            return Err("No such file or directory".to_string());
+        }
+        if target.len() > MAX_PATH_LEN {
+            return Err("Path too long".to_string());
+        }
+        self.insert_entry(scope, path, FileType::SymbolicLink, Some(target.to_string())).map(|_| ())
+    }
+
+    /// Target of the symbolic link at `path`
+    pub fn readlink(&self, path: &str) -> Result<String, String> {
+        self.readlink_at(PathScope::GLOBAL, path)
+    }
+
+    pub fn readlink_at(&self, scope: PathScope, path: &str) -> Result<String, String> {
+        match self.resolve_in(None, scope, path, false)? {
+            (inode, FileType::SymbolicLink) => {
+                self.links.read().get(&inode).cloned().ok_or_else(|| "No such file or directory".to_string())
+            }
+            _ => Err("Invalid argument".to_string()),
+        }
+    }
+
+    /// Add a name to the tree, failing if it exists: the lookup and the
+    /// insertion happen under one lock, which is what makes O_EXCL hold
+    /// between clients racing for the same name. Returns the new inode
+    fn insert_entry(
+        &self,
+        scope: PathScope,
+        path: &str,
+        file_type: FileType,
+        target: Option<String>,
+    ) -> Result<u64, String> {
         // TODO: Create actual file/directory in the mounted file system
-        
-        // Update cache
-        let mut cache = self.cache.write();
-        cache.insert(path.to_string(), inode);
+        let inode = {
+            let mut entries = self.entries.write();
+            let key = self.resolve_parent_in(&entries, scope, path)?;
+            let parent = key.0;
+            if entries.contains_key(&key) {
+                return Err(EEXIST.to_string());
+            }
+            let inode = self.next_inode.fetch_add(1, Ordering::SeqCst);
+            if let Some(target) = target {
+                self.links.write().insert(inode, target);
+            }
+            // Replaces a negative entry cached by an earlier failed lookup
+            self.dcache.write().invalidate(parent, &key.1);
+            entries.insert(key, (inode, file_type));
+            inode
+        };
         
         // Update statistics
         let mut stats = self.statistics.write();
         stats.create_count += 1;
         
-        Ok(())
+        Ok(inode)
     }
 
     /// Remove a file or directory (thread-safe)
-    pub fn remove(&mut self, path: &str) -> Result<(), String> {
+    pub fn remove(&self, path: &str) -> Result<(), String> {
+        self.remove_at(PathScope::GLOBAL, path)
+    }
+
+    /// Remove a file or directory in `scope`; a symbolic link as the last
+    /// component is removed itself
+    pub fn remove_at(&self, scope: PathScope, path: &str) -> Result<(), String> {
         // TODO: Remove actual file/directory from the mounted file system
-        
-        // Update cache
-        let mut cache = self.cache.write();
-        cache.remove(path);
+        {
+            let mut entries = self.entries.write();
+            let key = self.resolve_parent_in(&entries, scope, path)?;
+            let parent = key.0;
+            let (inode, file_type) = *entries.get(&key).ok_or_else(|| "No such file or directory".to_string())?;
+            if file_type == FileType::Directory && Self::has_children(&entries, inode) {
+                return Err("Directory not empty".to_string());
+            }
+            entries.remove(&key);
+            if file_type == FileType::SymbolicLink {
+                self.links.write().remove(&inode);
+            }
+            
+            let mut dcache = self.dcache.write();
+            dcache.invalidate(parent, &key.1);
+            if file_type == FileType::Directory {
+                dcache.invalidate_directory(inode);
+            }
+        }
         
         // Update statistics
         let mut stats = self.statistics.write();
@@ -435,14 +1051,90 @@ impl VirtualFileSystem {
         Ok(())
     }
 
-    /// Rename a file or directory (thread-safe)
-    pub fn rename(&mut self, old_path: &str, new_path: &str) -> Result<(), String> {
+    /// Rename a file or directory (thread-safe). Both paths are resolved
+    /// under the entries lock, so the rename is atomic to concurrent lookups
+    /// and namespace changes
+    pub fn rename(&self, old_path: &str, new_path: &str) -> Result<(), String> {
+        self.rename_at(PathScope::GLOBAL, old_path, new_path)
+    }
+
+    /// Rename within `scope`
+    pub fn rename_at(&self, scope: PathScope, old_path: &str, new_path: &str) -> Result<(), String> {
+        self.rename_entries(scope, old_path, new_path, 0, None)
+    }
+
+    /// Rename for a caller that locked the two parent directories it
+    /// resolved with parent_at, as renameat2 with RENAME_NOREPLACE in
+    /// `flags`. ESTALE when either path no longer lies in the directory
+    /// locked for it, after a concurrent rename moved one of them: the
+    /// caller resolves again and retries
+    pub fn rename_locked(
+        &self,
+        scope: PathScope,
+        old_path: &str,
+        new_path: &str,
+        flags: u32,
+        parents: (u64, u64),
+    ) -> Result<(), String> {
+        self.rename_entries(scope, old_path, new_path, flags, Some(parents))
+    }
+
+    fn rename_entries(
+        &self,
+        scope: PathScope,
+        old_path: &str,
+        new_path: &str,
+        flags: u32,
+        parents: Option<(u64, u64)>,
+    ) -> Result<(), String> {
+        if flags & !RENAME_NOREPLACE != 0 {
+            return Err(EINVAL.to_string());
+        }
+
         // TODO: Implement actual renaming in the mounted file system
-        
-        // Update cache
-        let mut cache = self.cache.write();
-        if let Some(inode) = cache.remove(old_path) {
-            cache.insert(new_path.to_string(), inode);
+        {
+            let mut entries = self.entries.write();
+            let old_key = self.resolve_parent_in(&entries, scope, old_path)?;
+            let new_key = self.resolve_parent_in(&entries, scope, new_path)?;
+            if parents.is_some_and(|parents| parents != (old_key.0, new_key.0)) {
+                return Err(ESTALE.to_string());
+            }
+            let entry = *entries.get(&old_key).ok_or_else(|| ENOENT.to_string())?;
+            // Moving a directory below itself would cut it off the tree;
+            // checked on inodes, as ".." and links hide it in the paths
+            if entry.1 == FileType::Directory && Self::is_within(&entries, new_key.0, entry.0) {
+                return Err(EINVAL.to_string());
+            }
+            if let Some(&(replaced, replaced_type)) = entries.get(&new_key) {
+                if flags & RENAME_NOREPLACE != 0 {
+                    return Err(EEXIST.to_string());
+                }
+                // Two names of one file: POSIX leaves both in place
+                if replaced == entry.0 {
+                    return Ok(());
+                }
+                match (entry.1 == FileType::Directory, replaced_type == FileType::Directory) {
+                    (true, false) => return Err(ENOTDIR.to_string()),
+                    (false, true) => return Err(EISDIR.to_string()),
+                    _ => {}
+                }
+                if replaced_type == FileType::Directory && Self::has_children(&entries, replaced) {
+                    return Err(ENOTEMPTY.to_string());
+                }
+                if replaced_type == FileType::SymbolicLink {
+                    self.links.write().remove(&replaced);
+                }
+                if replaced_type == FileType::Directory {
+                    self.dcache.write().invalidate_directory(replaced);
+                }
+            }
+            entries.remove(&old_key);
+            
+            // Children stay keyed by the inode of a moved directory
+            let mut dcache = self.dcache.write();
+            dcache.invalidate(old_key.0, &old_key.1);
+            dcache.invalidate(new_key.0, &new_key.1);
+            entries.insert(new_key, entry);
         }
         
         // Update statistics
@@ -452,29 +1144,195 @@ impl VirtualFileSystem {
         Ok(())
     }
 
-    /// Look up inode for a path (cached for performance)
-    fn lookup_inode(&self, path: &str) -> Result<u64, String> {
-        // Check cache first
-        {
-            let cache = self.cache.read();
-            if let Some(&inode) = cache.get(path) {
-                return Ok(inode);
+    /// Directory that holds the last component of `path`, for a caller
+    /// that locks it before changing the entry
+    pub fn parent_at(&self, scope: PathScope, path: &str) -> Result<u64, String> {
+        let entries = self.entries.read();
+        self.resolve_parent_in(&entries, scope, path).map(|(parent, _)| parent)
+    }
+
+    /// Whether `directory` is `ancestor` or lies below it. Entries are
+    /// keyed by parent, so each step up is a scan of the tree
+    fn is_within(entries: &DirectoryEntries, mut directory: u64, ancestor: u64) -> bool {
+        loop {
+            if directory == ancestor {
+                return true;
+            }
+            let parent = entries
+                .iter()
+                .find(|(_, &(inode, file_type))| inode == directory && file_type == FileType::Directory)
+                .map(|((parent, _), _)| *parent);
+            match parent {
+                Some(parent) => directory = parent,
+                None => return false,
             }
         }
+    }
+
+    fn has_children(entries: &DirectoryEntries, directory: u64) -> bool {
+        entries
+            .range((directory, String::new())..)
+            .next()
+            .is_some_and(|((parent, _), _)| *parent == directory)
+    }
+
+    /// Look up one component, through the dentry cache
+    fn lookup_component(&self, parent: u64, name: &str) -> Option<(u64, FileType)> {
+        if let Some(dentry) = self.dcache.write().lookup(parent, name) {
+            return match dentry {
+                Dentry::Positive { inode, file_type } => Some((inode, file_type)),
+                Dentry::Negative => None,
+            };
+        }
         
-        // TODO: Implement actual path resolution
-        let inode = 1; // Placeholder
-        
-        // Cache the result
-        let mut cache = self.cache.write();
-        cache.insert(path.to_string(), inode);
-        
-        Ok(inode)
+        // Hold the entries while caching so no create or remove slips in between
+        let entries = self.entries.read();
+        self.lookup_uncached(&entries, parent, name)
+    }
+
+    /// Look up one component in entries the caller holds locked
+    fn lookup_in(&self, entries: &DirectoryEntries, parent: u64, name: &str) -> Option<(u64, FileType)> {
+        let cached = self.dcache.write().lookup(parent, name);
+        match cached {
+            Some(Dentry::Positive { inode, file_type }) => Some((inode, file_type)),
+            Some(Dentry::Negative) => None,
+            None => self.lookup_uncached(entries, parent, name),
+        }
+    }
+
+    fn lookup_uncached(&self, entries: &DirectoryEntries, parent: u64, name: &str) -> Option<(u64, FileType)> {
+        let found = entries.get(&(parent, name.to_string())).copied();
+        let dentry = match found {
+            Some((inode, file_type)) => Dentry::Positive { inode, file_type },
+            None => Dentry::Negative,
+        };
+        self.dcache.write().insert(parent, name, dentry);
+        found
+    }
+
+    fn lookup(&self, entries: Option<&DirectoryEntries>, parent: u64, name: &str) -> Result<(u64, FileType), String> {
+        let found = match entries {
+            Some(entries) => self.lookup_in(entries, parent, name),
+            None => self.lookup_component(parent, name),
+        };
+        found.ok_or_else(|| "No such file or directory".to_string())
+    }
+
+    /// Continue a walk with the target of a symbolic link
+    fn follow_link(&self, scope: PathScope, walk: &mut PathWalk, link: u64) -> Result<(), String> {
+        walk.hops += 1;
+        if walk.hops > MAX_SYMLINK_HOPS {
+            return Err(ELOOP.to_string());
+        }
+        let target = self.links.read().get(&link).cloned().ok_or_else(|| "No such file or directory".to_string())?;
+        if target.starts_with('/') {
+            if scope.confined {
+                return Err(EXDEV.to_string());
+            }
+            walk.directories.truncate(1);
+        }
+        walk.push_front(&target)
+    }
+
+    /// Walk up to the last component, following symbolic links on the way
+    /// and resolving ".." against the directories actually walked through.
+    /// None when the path ends on a directory of the walk: the scope root
+    /// or a trailing ".."
+    fn walk_to_last(
+        &self,
+        entries: Option<&DirectoryEntries>,
+        scope: PathScope,
+        walk: &mut PathWalk,
+    ) -> Result<Option<String>, String> {
+        while let Some(component) = walk.pending.pop_front() {
+            if component == ".." {
+                if walk.directories.len() > 1 {
+                    walk.directories.pop();
+                } else if scope.confined {
+                    return Err(EXDEV.to_string());
+                }
+                continue;
+            }
+            if walk.pending.is_empty() {
+                return Ok(Some(component));
+            }
+            match self.lookup(entries, walk.directory(), &component)? {
+                (inode, FileType::Directory) => walk.directories.push(inode),
+                (inode, FileType::SymbolicLink) => self.follow_link(scope, walk, inode)?,
+                _ => return Err("Not a directory".to_string()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Resolve a path in `scope` to its inode, following a symbolic link in
+    /// the last component when `follow`
+    fn resolve_in(
+        &self,
+        entries: Option<&DirectoryEntries>,
+        scope: PathScope,
+        path: &str,
+        follow: bool,
+    ) -> Result<(u64, FileType), String> {
+        let mut walk = PathWalk::new(scope, path)?;
+        loop {
+            let name = match self.walk_to_last(entries, scope, &mut walk)? {
+                Some(name) => name,
+                None => return Ok((walk.directory(), FileType::Directory)),
+            };
+            match self.lookup(entries, walk.directory(), &name)? {
+                (inode, FileType::SymbolicLink) if follow => self.follow_link(scope, &mut walk, inode)?,
+                found => return Ok(found),
+            }
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Result<(u64, FileType), String> {
+        self.resolve_in(None, PathScope::GLOBAL, path, true)
+    }
+
+    /// Resolve the directory holding the last component of a path, in
+    /// entries the caller holds locked. The last component itself is not
+    /// followed
+    fn resolve_parent_in(
+        &self,
+        entries: &DirectoryEntries,
+        scope: PathScope,
+        path: &str,
+    ) -> Result<(u64, String), String> {
+        let mut walk = PathWalk::new(scope, path)?;
+        match self.walk_to_last(Some(entries), scope, &mut walk)? {
+            Some(name) => Ok((walk.directory(), name)),
+            None => Err("Invalid argument".to_string()),
+        }
+    }
+
+    /// Mounting over a directory changes what lies below it
+    fn invalidate_mount(&self, path: &str) {
+        if path == "/" {
+            self.dcache.write().invalidate_all();
+        } else if let Ok((inode, _)) = self.resolve(path) {
+            self.dcache.write().invalidate_directory(inode);
+        }
+    }
+
+    /// Resize the dentry cache
+    pub fn set_dcache_limits(&self, capacity: usize, negative_capacity: usize) {
+        self.dcache.write().set_limits(capacity, negative_capacity);
+    }
+
+    /// Get dentry cache statistics
+    pub fn get_dcache_statistics(&self) -> DcacheStatistics {
+        self.dcache.read().statistics()
     }
 
     /// Get VFS statistics
     pub fn get_statistics(&self) -> VfsStatistics {
-        self.statistics.read().clone()
+        let mut statistics = self.statistics.read().clone();
+        let dcache = self.get_dcache_statistics();
+        statistics.cache_hits = dcache.hits + dcache.negative_hits;
+        statistics.cache_misses = dcache.misses;
+        statistics
     }
 }
 
@@ -523,3 +1381,311 @@ fn get_current_timestamp() -> u64 {
     // TODO: Implement actual timestamp retrieval
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolves_through_dentry_cache() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/usr", FileType::Directory).unwrap();
+        vfs.create("/usr/bin", FileType::Directory).unwrap();
+        vfs.create("/usr/bin/ls", FileType::Regular).unwrap();
+
+        // PATH-style search: the miss in /bin is cached as a negative entry
+        assert!(vfs.get_attributes("/bin/ls").is_err());
+        assert!(vfs.get_attributes("/bin/ls").is_err());
+        assert_eq!(vfs.get_attributes("/usr/bin/../bin/./ls").unwrap().file_type, FileType::Regular);
+        assert_eq!(vfs.get_dcache_statistics().negative_hits, 1);
+
+        // Creating the missing name invalidates the negative entry
+        vfs.create("/bin", FileType::Directory).unwrap();
+        assert_eq!(vfs.get_attributes("/bin").unwrap().file_type, FileType::Directory);
+        assert!(vfs.create("/bin", FileType::Directory).is_err());
+        assert!(vfs.get_attributes("/usr/bin/ls/x").is_err());
+    }
+
+    #[test]
+    fn test_invalidates_on_rename_and_remove() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/etc", FileType::Directory).unwrap();
+        vfs.create("/etc/hosts", FileType::Regular).unwrap();
+        let inode = vfs.get_attributes("/etc/hosts").unwrap().inode;
+
+        vfs.rename("/etc", "/config").unwrap();
+        assert!(vfs.get_attributes("/etc/hosts").is_err());
+        assert_eq!(vfs.get_attributes("/config/hosts").unwrap().inode, inode);
+        assert!(vfs.rename("/config", "/config/sub").is_err());
+
+        assert!(vfs.remove("/config").is_err());
+        vfs.remove("/config/hosts").unwrap();
+        assert!(vfs.get_attributes("/config/hosts").is_err());
+        vfs.remove("/config").unwrap();
+        assert!(vfs.get_attributes("/config").is_err());
+        assert!(vfs.get_dcache_statistics().invalidations >= 3);
+    }
+
+    #[test]
+    fn test_follows_symlinks_with_loop_detection() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/usr", FileType::Directory).unwrap();
+        vfs.create("/usr/lib", FileType::Directory).unwrap();
+        vfs.create("/usr/lib/libc.so", FileType::Regular).unwrap();
+        vfs.symlink("/usr/lib", "/lib").unwrap();
+        vfs.symlink("../lib/libc.so", "/usr/lib/current").unwrap();
+        vfs.symlink("libc.so", "/usr/lib/libc.so.6").unwrap();
+
+        let libc = vfs.get_attributes("/usr/lib/libc.so").unwrap().inode;
+        assert_eq!(vfs.get_attributes("/lib/libc.so.6").unwrap().inode, libc);
+        // ".." is taken from the directory the link was found in, not lexically
+        assert_eq!(vfs.get_attributes("/lib/current").unwrap().inode, libc);
+        assert_eq!(vfs.readlink("/lib/libc.so.6").unwrap(), "libc.so");
+        assert!(vfs.readlink("/lib/libc.so").is_err());
+
+        // lstat and O_NOFOLLOW see the link itself
+        let link = vfs.get_attributes_at(PathScope::GLOBAL, "/lib", AT_SYMLINK_NOFOLLOW).unwrap();
+        assert_eq!((link.file_type, link.size), (FileType::SymbolicLink, 8));
+        assert!(vfs.open("/lib/libc.so.6", OpenFlags::from_flags(0o1)).is_ok());
+        assert_eq!(vfs.open("/lib/libc.so.6", OpenFlags::from_flags(0o101)).unwrap_err(), ELOOP);
+        // ...but only in the last component
+        assert!(vfs.open("/lib/libc.so", OpenFlags::from_flags(0o101)).is_ok());
+
+        vfs.symlink("/loop/b", "/a").unwrap();
+        vfs.symlink("/a", "/b").unwrap();
+        vfs.create("/loop", FileType::Directory).unwrap();
+        vfs.symlink("/a", "/loop/b").unwrap();
+        assert_eq!(vfs.get_attributes("/a").unwrap_err(), ELOOP);
+        assert_eq!(vfs.create("/a/x", FileType::Regular).unwrap_err(), ELOOP);
+
+        // Removing a link leaves its target alone
+        vfs.remove("/lib").unwrap();
+        assert!(vfs.get_attributes("/lib/libc.so").is_err());
+        assert_eq!(vfs.get_attributes("/usr/lib/libc.so").unwrap().inode, libc);
+    }
+
+    #[test]
+    fn test_confines_resolution_to_a_scope() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/etc", FileType::Directory).unwrap();
+        vfs.create("/etc/shadow", FileType::Regular).unwrap();
+        vfs.create("/srv", FileType::Directory).unwrap();
+        vfs.create("/srv/app", FileType::Directory).unwrap();
+        vfs.create("/srv/app/data", FileType::Directory).unwrap();
+        vfs.create("/srv/app/data/db", FileType::Regular).unwrap();
+        vfs.symlink("data/db", "/srv/app/current").unwrap();
+        vfs.symlink("/etc/shadow", "/srv/app/absolute").unwrap();
+        vfs.symlink("../../etc/shadow", "/srv/app/climb").unwrap();
+
+        let scope = vfs.scope_at(PathScope::GLOBAL, "/srv/app").unwrap();
+        let db = vfs.get_attributes("/srv/app/data/db").unwrap().inode;
+        assert_eq!(vfs.get_attributes_at(scope, "current", 0).unwrap().inode, db);
+        assert_eq!(vfs.get_attributes_at(scope, "data/../data/db", 0).unwrap().inode, db);
+        assert_eq!(vfs.get_attributes_at(scope, "", 0).unwrap().inode, scope.root());
+
+        for escape in ["/etc/shadow", "..", "data/../../..", "absolute", "climb"] {
+            assert_eq!(vfs.get_attributes_at(scope, escape, 0).unwrap_err(), EXDEV, "{}", escape);
+        }
+        assert_eq!(vfs.create_at(scope, "../planted", FileType::Regular).unwrap_err(), EXDEV);
+        assert_eq!(vfs.rename_at(scope, "data/db", "climb/x").unwrap_err(), EXDEV);
+
+        // The link itself may still be looked at and removed
+        assert_eq!(vfs.readlink_at(scope, "absolute").unwrap(), "/etc/shadow");
+        vfs.remove_at(scope, "absolute").unwrap();
+
+        // Scopes nest, each confined to its own subtree
+        let data = vfs.scope_at(scope, "data").unwrap();
+        assert!(vfs.open_at(data, "db", OpenFlags::from_flags(0o1)).is_ok());
+        assert_eq!(vfs.get_attributes_at(data, "../current", 0).unwrap_err(), EXDEV);
+    }
+
+    /// Backend keeping files in memory, by path relative to the mount
+    #[derive(Default)]
+    struct MemoryBackend {
+        files: spin::Mutex<BTreeMap<String, Vec<u8>>>,
+        opened: spin::Mutex<Vec<String>>,
+    }
+
+    impl MountedFileSystem for MemoryBackend {
+        fn open(&self, path: &str, flags: OpenFlags) -> Result<u64, String> {
+            let mut files = self.files.lock();
+            if !files.contains_key(path) && !flags.is_create() {
+                return Err("No such file or directory".to_string());
+            }
+            files.entry(path.to_string()).or_default();
+            let mut opened = self.opened.lock();
+            opened.push(path.to_string());
+            Ok(opened.len() as u64 - 1)
+        }
+
+        fn read_at(&self, file: u64, offset: u64, buffer: &mut [u8]) -> Result<usize, String> {
+            let path = self.opened.lock()[file as usize].clone();
+            let files = self.files.lock();
+            let data = files[&path].get(offset as usize..).unwrap_or(&[]);
+            let length = data.len().min(buffer.len());
+            buffer[..length].copy_from_slice(&data[..length]);
+            Ok(length)
+        }
+
+        fn write_at(&self, file: u64, offset: u64, buffer: &[u8]) -> Result<usize, String> {
+            let path = self.opened.lock()[file as usize].clone();
+            let mut files = self.files.lock();
+            let data = files.get_mut(&path).unwrap();
+            data.resize(data.len().max(offset as usize + buffer.len()), 0);
+            data[offset as usize..offset as usize + buffer.len()].copy_from_slice(buffer);
+            Ok(buffer.len())
+        }
+
+        fn sync(&self, _file: u64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn close(&self, _file: u64) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn get_attributes(&self, path: &str) -> Result<FileAttributes, String> {
+            if path == "/" {
+                return Ok(FileAttributes::new(1, FileType::Directory));
+            }
+            let files = self.files.lock();
+            let data = files.get(path).ok_or_else(|| "No such file or directory".to_string())?;
+            let mut attributes = FileAttributes::new(2, FileType::Regular);
+            attributes.size = data.len() as u64;
+            Ok(attributes)
+        }
+
+        fn read_directory(&self, _path: &str) -> Result<Vec<DirEntry>, String> {
+            Ok(self
+                .files
+                .lock()
+                .keys()
+                .enumerate()
+                .map(|(index, name)| DirEntry {
+                    name: name[1..].to_string(),
+                    inode: 2,
+                    file_type: FileType::Regular,
+                    offset: index as u64,
+                    name_len: name.len() as u8 - 1,
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_dispatches_to_mounted_backends() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/mnt", FileType::Directory).unwrap();
+        vfs.create("/mnt/share", FileType::Directory).unwrap();
+        vfs.create("/mnt/shared", FileType::Regular).unwrap();
+        let backend = Arc::new(MemoryBackend::default());
+        vfs.mount_with("/mnt/share/", FileSystemType::NFS, "10.0.0.5:/srv", "", backend.clone()).unwrap();
+        assert_eq!(vfs.mount_with("/mnt/share", FileSystemType::NFS, "", "", backend.clone()).unwrap_err(), EBUSY);
+
+        let file = vfs.open("/mnt/share/notes.txt", OpenFlags::from_flags(0o13)).unwrap();
+        assert_eq!(vfs.write(file, b"remote").unwrap(), 6);
+        let mut buffer = [0u8; 16];
+        assert_eq!(vfs.read_at(file, 2, &mut buffer).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"mote");
+        assert_eq!(vfs.get_attributes("/mnt/share/notes.txt").unwrap().size, 6);
+        assert_eq!(vfs.get_attributes("/mnt/share").unwrap().file_type, FileType::Directory);
+        assert_eq!(vfs.read_directory("/mnt/share").unwrap()[0].name, "notes.txt");
+
+        // Siblings sharing the prefix and scoped lookups stay in the tree
+        assert_eq!(vfs.get_attributes("/mnt/shared").unwrap().file_type, FileType::Regular);
+        let mnt = vfs.scope_at(PathScope::GLOBAL, "/mnt").unwrap();
+        assert!(vfs.get_attributes_at(mnt, "share/notes.txt", 0).is_err());
+        assert_eq!(vfs.get_attributes("/mnt/share/../shared").unwrap_err(), EXDEV);
+
+        assert_eq!(vfs.unmount("/mnt/share").unwrap_err(), EBUSY);
+        vfs.close(file).unwrap();
+        vfs.unmount("/mnt/share").unwrap();
+        assert!(vfs.get_attributes("/mnt/share/notes.txt").is_err());
+    }
+
+    #[test]
+    fn test_namespaces_keep_their_own_mounts() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/srv", FileType::Directory).unwrap();
+        vfs.create("/srv/app", FileType::Directory).unwrap();
+        vfs.create("/srv/app/data", FileType::Directory).unwrap();
+        vfs.create("/srv/app/run.conf", FileType::Regular).unwrap();
+        vfs.create("/etc", FileType::Directory).unwrap();
+        let shared = Arc::new(MemoryBackend::default());
+        vfs.mount_with("/srv/app/data", FileSystemType::NFS, "10.0.0.5:/data", "", shared.clone()).unwrap();
+        let file = vfs.open("/srv/app/data/db", OpenFlags::from_flags(0o13)).unwrap();
+        vfs.close(file).unwrap();
+
+        // Rooted at /srv/app with the mounts found below it
+        assert!(vfs.namespace_scope(7).is_none());
+        vfs.create_namespace(7, INITIAL_NAMESPACE, "/srv/app").unwrap();
+        assert_eq!(vfs.create_namespace(7, INITIAL_NAMESPACE, "/").unwrap_err(), EEXIST);
+        assert!(vfs.create_namespace(8, 9, "/").is_err());
+        let scope = vfs.namespace_scope(7).unwrap();
+        assert_eq!(scope.namespace(), 7);
+        assert_eq!(vfs.get_attributes_at(scope, "/run.conf", 0).unwrap().file_type, FileType::Regular);
+        assert!(vfs.get_attributes_at(scope, "/../../etc", 0).is_err());
+        assert_eq!(vfs.get_attributes_at(scope, "/data/db", 0).unwrap().size, 0);
+
+        // Mounts made inside stay inside
+        let private = Arc::new(MemoryBackend::default());
+        vfs.create("/srv/app/tmp", FileType::Directory).unwrap();
+        vfs.mount_in(7, "/tmp", FileSystemType::NFS, "", "", private.clone()).unwrap();
+        let file = vfs.open_at(scope, "/tmp/scratch", OpenFlags::from_flags(0o13)).unwrap();
+        vfs.close(file).unwrap();
+        assert!(vfs.get_attributes("/srv/app/tmp/scratch").is_err());
+        assert!(vfs.get_attributes("/tmp/scratch").is_err());
+
+        // Unmounting in one namespace leaves the other's mount in place
+        vfs.unmount_in(7, "/data").unwrap();
+        assert!(vfs.get_attributes_at(scope, "/data/db", 0).is_err());
+        assert!(vfs.get_attributes("/srv/app/data/db").is_ok());
+
+        assert_eq!(vfs.drop_namespace(INITIAL_NAMESPACE).unwrap_err(), EBUSY);
+        vfs.drop_namespace(7).unwrap();
+        assert!(vfs.namespace_scope(7).is_none());
+        assert!(vfs.get_attributes("/srv/app/data/db").is_ok());
+    }
+
+    #[test]
+    fn test_renames_and_links_atomically() {
+        let vfs = VirtualFileSystem::new();
+        vfs.create("/a", FileType::Directory).unwrap();
+        vfs.create("/a/b", FileType::Directory).unwrap();
+        vfs.create("/c", FileType::Directory).unwrap();
+        vfs.create("/a/file", FileType::Regular).unwrap();
+        let scope = PathScope::GLOBAL;
+
+        // Only one O_CREAT | O_EXCL open wins a name
+        let (file, created) = vfs.create_open_at(scope, "/c/new", OpenFlags::from_flags(0o52)).unwrap();
+        assert!(created);
+        assert_eq!(vfs.create_open_at(scope, "/c/new", OpenFlags::from_flags(0o52)).unwrap_err(), EEXIST);
+        let (other, created) = vfs.create_open_at(scope, "/c/new", OpenFlags::from_flags(0o12)).unwrap();
+        assert!(!created);
+        vfs.close(other).unwrap();
+        vfs.close(file).unwrap();
+
+        // A directory cannot go below itself, even reached through a link
+        vfs.symlink("/a/b", "/c/down").unwrap();
+        assert_eq!(vfs.rename("/a", "/c/down/a").unwrap_err(), EINVAL);
+        assert_eq!(vfs.rename("/c", "/a/file").unwrap_err(), ENOTDIR);
+        assert_eq!(vfs.rename("/a/file", "/c").unwrap_err(), EISDIR);
+        assert_eq!(vfs.rename_locked(scope, "/a/file", "/c/new", RENAME_NOREPLACE, (0, 0)).unwrap_err(), ESTALE);
+        let (a, c) = (vfs.parent_at(scope, "/a/file").unwrap(), vfs.parent_at(scope, "/c/new").unwrap());
+        assert_eq!(vfs.rename_locked(scope, "/a/file", "/c/new", RENAME_NOREPLACE, (a, c)).unwrap_err(), EEXIST);
+        vfs.rename_locked(scope, "/a/file", "/c/new", 0, (a, c)).unwrap();
+        assert!(vfs.get_attributes("/a/file").is_err());
+
+        // An unnamed file gets a name only once and never over another file
+        let tmp = vfs.open_tmpfile_at(scope, "/c", OpenFlags::from_flags(0o202)).unwrap();
+        assert_eq!(vfs.link_at(tmp, scope, "/c/new").unwrap_err(), EEXIST);
+        vfs.link_at(tmp, scope, "/c/saved").unwrap();
+        assert_eq!(vfs.link_at(tmp, scope, "/c/again").unwrap_err(), EINVAL);
+        vfs.close(tmp).unwrap();
+        assert_eq!(vfs.get_attributes("/c/saved").unwrap().file_type, FileType::Regular);
+        let private = vfs.open_tmpfile_at(scope, "/c", OpenFlags::from_flags(0o242)).unwrap();
+        assert_eq!(vfs.link_at(private, scope, "/c/private").unwrap_err(), ENOENT);
+        vfs.close(private).unwrap();
+        assert_eq!(vfs.open_tmpfile_at(scope, "/c/saved", OpenFlags::from_flags(0o202)).unwrap_err(), ENOTDIR);
+    }
+}
@@ -0,0 +1,297 @@
+/*
+ * Orion Operating System - File System Server Workers
+ *
+ * Requests are served by a pool of worker tasks on the orion_async
+ * executor rather than one after the other: the receive loop queues
+ * each request and returns to the IPC channel, and a worker waiting on
+ * a lock or on the VFS leaves the others running. VFS operations are
+ * run through spawn_blocking, so with helper threads installed as the
+ * blocking backend they also run in parallel.
+ *
+ * Ordering is kept where POSIX asks for it. Operations on one open file
+ * hold that file's lock, which is handed over in arrival order, so
+ * writes through a handle land in the order they were submitted and a
+ * read never sees half of one. Doorbells on one ring hold the ring, as
+ * its entries are consumed in order.
+ *
+ * Requests that change a directory (create, link, rename) hold the lock
+ * of that directory, so clients changing one directory are served in
+ * arrival order. A rename holds both directories it touches, taken in
+ * inode order so two renames crossing the same pair in opposite
+ * directions cannot deadlock; a rename between two directories first
+ * takes the pool-wide rename lock, as Linux does, so no other such
+ * rename can reshape the tree while it checks that a directory is not
+ * moved below itself. The VFS still resolves and updates the namespace
+ * under its own lock, which keeps each change atomic to lookups; a
+ * rename whose directories moved before it got their locks is resolved
+ * again. Every wait for an ordering lock is counted and timed.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use orion_async::{AsyncChannel, AsyncMutex, AsyncMutexGuard};
+use spin::Mutex;
+
+/// Workers started when the server is not told otherwise
+pub const DEFAULT_WORKERS: usize = 4;
+pub const MAX_WORKERS: usize = 32;
+
+/// Requests queued per worker before the receive loop waits
+const QUEUE_PER_WORKER: usize = 16;
+
+/// Waits for ordering locks and load of the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentionStatistics {
+    pub requests: u64,
+    /// Requests queued or being served at once, at most
+    pub max_in_flight: u64,
+    /// Requests the receive loop had to wait to queue
+    pub queue_full: u64,
+    pub file_acquisitions: u64,
+    /// Acquisitions that waited behind another request on the same file
+    pub file_contended: u64,
+    pub ring_acquisitions: u64,
+    pub ring_contended: u64,
+    /// Directory and rename locks, and the waits among them
+    pub directory_acquisitions: u64,
+    pub directory_contended: u64,
+    /// Time spent waiting for ordering locks, in total and at most once
+    pub wait_ns: u64,
+    pub max_wait_ns: u64,
+}
+
+impl ContentionStatistics {
+    /// Little-endian u64 fields in declaration order, for the stats reply
+    pub fn encode(&self) -> Vec<u8> {
+        let fields = [
+            self.requests,
+            self.max_in_flight,
+            self.queue_full,
+            self.file_acquisitions,
+            self.file_contended,
+            self.ring_acquisitions,
+            self.ring_contended,
+            self.directory_acquisitions,
+            self.directory_contended,
+            self.wait_ns,
+            self.max_wait_ns,
+        ];
+        fields.iter().flat_map(|field| field.to_le_bytes()).collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LockKind {
+    File,
+    Ring,
+    Directory,
+}
+
+/// Queue of requests feeding the worker tasks
+pub struct WorkerPool<T> {
+    queue: AsyncChannel<T>,
+    workers: usize,
+    in_flight: AtomicUsize,
+    files: Mutex<BTreeMap<u64, Arc<AsyncMutex<()>>>>,
+    directories: Mutex<BTreeMap<u64, Arc<AsyncMutex<()>>>>,
+    rename: AsyncMutex<()>,
+    statistics: Mutex<ContentionStatistics>,
+}
+
+impl<T> WorkerPool<T> {
+    /// Pool for `workers` tasks, clamped to 1..=MAX_WORKERS
+    pub fn new(workers: usize) -> Self {
+        let workers = workers.clamp(1, MAX_WORKERS);
+        Self {
+            queue: AsyncChannel::new(workers * QUEUE_PER_WORKER),
+            workers,
+            in_flight: AtomicUsize::new(0),
+            files: Mutex::new(BTreeMap::new()),
+            directories: Mutex::new(BTreeMap::new()),
+            rename: AsyncMutex::new(()),
+            statistics: Mutex::new(ContentionStatistics::default()),
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Requests queued or being served
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    pub fn statistics(&self) -> ContentionStatistics {
+        *self.statistics.lock()
+    }
+
+    /// Queue a request, waiting while every worker is backed up
+    pub async fn dispatch(&self, request: T) {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::AcqRel) + 1;
+        {
+            let mut statistics = self.statistics.lock();
+            statistics.requests += 1;
+            statistics.max_in_flight = statistics.max_in_flight.max(in_flight as u64);
+        }
+        let request = match self.queue.try_send(request) {
+            Ok(()) => return,
+            Err(error) => error.0,
+        };
+        self.statistics.lock().queue_full += 1;
+        if self.queue.send(request).await.is_err() {
+            self.in_flight.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Next request for a worker, None once the pool is closed
+    pub async fn next(&self) -> Option<T> {
+        self.queue.recv().await.ok()
+    }
+
+    /// A worker is done with the request it took
+    pub fn finish(&self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Let the workers drain the queue and stop
+    pub fn close(&self) {
+        self.queue.close();
+    }
+
+    /// Ordering lock of an open file, held across one operation on it
+    pub fn file_lock(&self, handle: u64) -> Arc<AsyncMutex<()>> {
+        self.files.lock().entry(handle).or_insert_with(|| Arc::new(AsyncMutex::new(()))).clone()
+    }
+
+    /// The file was closed; requests still holding its lock keep it alive
+    pub fn forget_file(&self, handle: u64) {
+        self.files.lock().remove(&handle);
+    }
+
+    /// Take a file's ordering lock, counting the wait
+    pub async fn order_file<'a>(&self, lock: &'a AsyncMutex<()>) -> AsyncMutexGuard<'a, ()> {
+        self.acquire(LockKind::File, lock).await
+    }
+
+    /// Ordering lock of the directory `inode`, held while a request
+    /// changes its entries
+    pub fn directory_lock(&self, inode: u64) -> Arc<AsyncMutex<()>> {
+        self.directories.lock().entry(inode).or_insert_with(|| Arc::new(AsyncMutex::new(()))).clone()
+    }
+
+    /// Drop the lock of a directory once no request holds or waits for it
+    pub fn release_directory(&self, inode: u64, lock: Arc<AsyncMutex<()>>) {
+        let mut directories = self.directories.lock();
+        // The table's reference and the caller's
+        if Arc::strong_count(&lock) == 2 {
+            directories.remove(&inode);
+        }
+    }
+
+    /// Take a directory's lock, counting the wait. Requests holding two
+    /// take the one of the lower inode first
+    pub async fn order_directory<'a>(&self, lock: &'a AsyncMutex<()>) -> AsyncMutexGuard<'a, ()> {
+        self.acquire(LockKind::Directory, lock).await
+    }
+
+    /// The rename lock, held across a rename between two directories
+    /// before their own locks
+    pub async fn order_rename(&self) -> AsyncMutexGuard<'_, ()> {
+        self.acquire(LockKind::Directory, &self.rename).await
+    }
+
+    /// Take a ring's lock, counting the wait
+    pub async fn order_ring<'a, R>(&self, lock: &'a AsyncMutex<R>) -> AsyncMutexGuard<'a, R> {
+        self.acquire(LockKind::Ring, lock).await
+    }
+
+    async fn acquire<'a, R>(&self, kind: LockKind, lock: &'a AsyncMutex<R>) -> AsyncMutexGuard<'a, R> {
+        let (guard, waited) = match lock.try_lock() {
+            Some(guard) => (guard, None),
+            None => {
+                let start = orion_async::now();
+                let guard = lock.lock().await;
+                (guard, Some(orion_async::now().saturating_sub(start)))
+            }
+        };
+
+        let mut locked = self.statistics.lock();
+        let statistics = &mut *locked;
+        let (acquisitions, contended) = match kind {
+            LockKind::File => (&mut statistics.file_acquisitions, &mut statistics.file_contended),
+            LockKind::Ring => (&mut statistics.ring_acquisitions, &mut statistics.ring_contended),
+            LockKind::Directory => (&mut statistics.directory_acquisitions, &mut statistics.directory_contended),
+        };
+        *acquisitions += 1;
+        if let Some(waited) = waited {
+            *contended += 1;
+            statistics.wait_ns += waited;
+            statistics.max_wait_ns = statistics.max_wait_ns.max(waited);
+        }
+        guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+    use orion_async::{block_on, spawn, yield_now};
+
+    #[test]
+    fn test_serializes_requests_on_one_file() {
+        let pool = Rc::new(WorkerPool::new(3));
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        block_on(async {
+            let mut workers = Vec::new();
+            for _ in 0..pool.workers() {
+                let (pool, log) = (pool.clone(), log.clone());
+                workers.push(spawn(async move {
+                    while let Some((handle, id)) = pool.next().await {
+                        let lock = pool.file_lock(handle);
+                        let _order = pool.order_file(&lock).await;
+                        log.borrow_mut().push((handle, id, true));
+                        yield_now().await;
+                        log.borrow_mut().push((handle, id, false));
+                        pool.finish();
+                    }
+                }));
+            }
+
+            for id in 0..4 {
+                pool.dispatch((1u64, id)).await;
+            }
+            pool.dispatch((2u64, 4)).await;
+            pool.close();
+            for worker in workers {
+                worker.await;
+            }
+        });
+
+        // Operations on file 1 never overlap and run in submission order
+        let file1: Vec<(u32, bool)> =
+            log.borrow().iter().filter(|entry| entry.0 == 1).map(|entry| (entry.1, entry.2)).collect();
+        let expected: Vec<(u32, bool)> = (0..4).flat_map(|id| [(id, true), (id, false)]).collect();
+        assert_eq!(file1, expected);
+
+        let statistics = pool.statistics();
+        assert_eq!(pool.in_flight(), 0);
+        assert_eq!(statistics.requests, 5);
+        assert_eq!(statistics.file_acquisitions, 5);
+        assert!(statistics.file_contended >= 1);
+        assert_eq!(WorkerPool::<u8>::new(0).workers(), 1);
+        assert_eq!(WorkerPool::<u8>::new(1000).workers(), MAX_WORKERS);
+    }
+}
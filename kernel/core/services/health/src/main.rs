@@ -0,0 +1,162 @@
+/*
+ * Orion Operating System - Health Server
+ *
+ * Aggregates the liveness and readiness of the system servers. Every
+ * few seconds the server sends CHECK to each watched server (see
+ * watch.rs) and keeps the results; STATUS hands them out as one report
+ * whose state is the worst of the servers' (see protocol.rs).
+ *
+ * The metrics exporter serves the report as GET /healthz for load
+ * balancers. Init reads it for its restart policies: a server whose
+ * consecutive failures reach the limit of its policy is restarted, and
+ * the servers depending on it are held back while it is not ready.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_health::protocol::encode_report;
+use orion_health::{HealthChecks, Transport};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_READ, CAP_WRITE};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the watched servers and their results
+const HEAP_SIZE: usize = 512 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod protocol;
+mod watch;
+
+use protocol::*;
+use watch::Watchlist;
+
+const POLL_INTERVAL_NS: u64 = 100 * 1_000_000;
+
+/// Polls of the channel between two checks of the servers (five seconds)
+const CHECK_POLLS: u64 = 50;
+
+/// IPC channel to a watched server
+struct ServerIpc(IpcChannel);
+
+impl Transport for ServerIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn connect(name: &str) -> ServerIpc {
+    ServerIpc(IpcChannel::connect(name))
+}
+
+fn servers_checked(server: &HealthServer) -> Result<(), String> {
+    if server.watchlist.polls() > 0 {
+        Ok(())
+    } else {
+        Err(String::from("servers not checked yet"))
+    }
+}
+
+struct HealthServer {
+    watchlist: Watchlist<ServerIpc>,
+    health: HealthChecks<HealthServer>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+    polls: u64,
+}
+
+impl HealthServer {
+    fn new() -> Self {
+        Self {
+            watchlist: Watchlist::new(connect),
+            health: HealthChecks::new().readiness("checked", servers_checked),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+            polls: 0,
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+
+            if self.polls % CHECK_POLLS == 0 {
+                self.watchlist.poll();
+            }
+            self.polls += 1;
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let Some(request) = HealthRequest::decode(&message.data) else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+            return;
+        };
+
+        let rights = match request {
+            HealthRequest::Status | HealthRequest::Check => CAP_READ,
+            HealthRequest::Register { .. } | HealthRequest::Unregister { .. } => CAP_WRITE,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let response = match request {
+            HealthRequest::Status => {
+                let mut payload = Vec::new();
+                encode_report(&self.watchlist.report(), &mut payload);
+                reply(STATUS_OK, &payload)
+            }
+            HealthRequest::Register { name } => match self.watchlist.register(&name) {
+                Ok(()) => reply(STATUS_OK, &[]),
+                Err(status) => reply(status, &[]),
+            },
+            HealthRequest::Unregister { name } => match self.watchlist.unregister(&name) {
+                Ok(()) => reply(STATUS_OK, &[]),
+                Err(status) => reply(status, &[]),
+            },
+            HealthRequest::Check => match self.health.serve(self, &message.data) {
+                Some(response) => response,
+                None => reply(STATUS_EINVAL, &[]),
+            },
+        };
+        self.ipc_channel.send(message.sender, &response);
+    }
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = HealthServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * Orion Operating System - Health Server Protocol
+ *
+ * IPC requests of the health server; the wire format is the one of
+ * orion_health::protocol.
+ *
+ *   STATUS      (none)  -> state:u32 count:u32 {server}*
+ *   REGISTER    name    -> (empty)
+ *   UNREGISTER  name    -> (empty)
+ *   CHECK       (none)  -> count:u32 {check}*
+ *
+ * STATUS answers the results of the last poll, not a new one. REGISTER
+ * adds the server listening on channel `name` to those polled, and
+ * succeeds when it already is; UNREGISTER removes it, EPERM for the
+ * servers polled from the start. CHECK is the health server's own.
+ *
+ * STATUS and CHECK need CAP_READ, REGISTER and UNREGISTER CAP_WRITE.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_health::protocol::read_string;
+use orion_health::{HEALTH_OP_CHECK, HEALTH_OP_REGISTER, HEALTH_OP_STATUS, HEALTH_OP_UNREGISTER};
+
+pub use orion_health::protocol::{STATUS_EINVAL, STATUS_ENOENT, STATUS_ENOSPC, STATUS_EPERM, STATUS_OK};
+
+/// Longest channel name a server registers under
+pub const MAX_NAME: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HealthRequest {
+    Status,
+    Register { name: String },
+    Unregister { name: String },
+    Check,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_name(data: &[u8]) -> Option<String> {
+    let (name, _) = read_string(data, 4)?;
+    (!name.is_empty() && name.len() <= MAX_NAME).then_some(name)
+}
+
+impl HealthRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            HEALTH_OP_STATUS => Some(HealthRequest::Status),
+            HEALTH_OP_REGISTER => Some(HealthRequest::Register { name: read_name(data)? }),
+            HEALTH_OP_UNREGISTER => Some(HealthRequest::Unregister { name: read_name(data)? }),
+            HEALTH_OP_CHECK => Some(HealthRequest::Check),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_health::protocol::put_string;
+
+    fn request(opcode: u32, name: &str) -> Vec<u8> {
+        let mut data = opcode.to_le_bytes().to_vec();
+        put_string(&mut data, name);
+        data
+    }
+
+    #[test]
+    fn test_decodes_requests() {
+        assert_eq!(HealthRequest::decode(&HEALTH_OP_STATUS.to_le_bytes()), Some(HealthRequest::Status));
+        assert_eq!(HealthRequest::decode(&HEALTH_OP_CHECK.to_le_bytes()), Some(HealthRequest::Check));
+        assert_eq!(
+            HealthRequest::decode(&request(HEALTH_OP_REGISTER, "crashd")),
+            Some(HealthRequest::Register { name: String::from("crashd") })
+        );
+        assert_eq!(HealthRequest::decode(&request(HEALTH_OP_UNREGISTER, "")), None);
+        assert_eq!(HealthRequest::decode(&request(HEALTH_OP_REGISTER, &"x".repeat(MAX_NAME + 1))), None);
+        assert_eq!(HealthRequest::decode(&[1, 0]), None);
+        assert_eq!(HealthRequest::decode(&9u32.to_le_bytes()), None);
+    }
+}
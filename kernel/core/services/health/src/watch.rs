@@ -0,0 +1,170 @@
+/*
+ * Orion Operating System - Watched Servers
+ *
+ * The servers the health server polls and what it last found. The file
+ * system, network and storage servers are watched from the start; other
+ * servers register themselves once they serve requests.
+ *
+ * A server that does not answer its CHECK counts as a failed liveness
+ * check. Consecutive unhealthy polls are counted so that a restart
+ * policy can wait for a few before acting on a slow server.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_health::{CheckResult, HealthClient, HealthReport, HealthState, Probe, ServerHealth, Transport};
+
+use crate::protocol::{STATUS_ENOENT, STATUS_ENOSPC, STATUS_EPERM};
+
+/// Servers polled from the start: file system, network and storage
+pub const BUILTIN_SERVERS: [&str; 3] = ["fs", "net", "lvm-advanced"];
+
+/// Most servers watched at once
+pub const MAX_SERVERS: usize = 32;
+
+struct Watched<T: Transport> {
+    health: ServerHealth,
+    builtin: bool,
+    client: HealthClient<T>,
+}
+
+pub struct Watchlist<T: Transport> {
+    servers: Vec<Watched<T>>,
+    /// Transport to the server listening on a channel
+    connect: fn(&str) -> T,
+    polls: u64,
+}
+
+impl<T: Transport> Watchlist<T> {
+    /// Watch the built-in servers, reaching them through `connect`
+    pub fn new(connect: fn(&str) -> T) -> Self {
+        let mut list = Self { servers: Vec::new(), connect, polls: 0 };
+        for name in BUILTIN_SERVERS {
+            list.add(name, true);
+        }
+        list
+    }
+
+    fn add(&mut self, name: &str, builtin: bool) {
+        // Not ready until its first poll
+        let health =
+            ServerHealth { name: String::from(name), state: HealthState::NotReady, failures: 0, checks: vec![] };
+        self.servers.push(Watched { health, builtin, client: HealthClient::new((self.connect)(name)) });
+    }
+
+    pub fn register(&mut self, name: &str) -> Result<(), i32> {
+        if self.servers.iter().any(|server| server.health.name == name) {
+            return Ok(());
+        }
+        if self.servers.len() >= MAX_SERVERS {
+            return Err(STATUS_ENOSPC);
+        }
+        self.add(name, false);
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Result<(), i32> {
+        let index = self.servers.iter().position(|server| server.health.name == name).ok_or(STATUS_ENOENT)?;
+        if self.servers[index].builtin {
+            return Err(STATUS_EPERM);
+        }
+        self.servers.remove(index);
+        Ok(())
+    }
+
+    /// Check every server
+    pub fn poll(&mut self) {
+        for server in self.servers.iter_mut() {
+            let checks = server.client.check().unwrap_or_else(|status| {
+                vec![CheckResult {
+                    probe: Probe::Liveness,
+                    name: String::from("answer"),
+                    passed: false,
+                    detail: format!("no answer to CHECK (error {})", status),
+                }]
+            });
+            let health = &mut server.health;
+            health.state = HealthState::of_checks(&checks);
+            health.failures =
+                if health.state == HealthState::Unhealthy { health.failures.saturating_add(1) } else { 0 };
+            health.checks = checks;
+        }
+        self.polls += 1;
+    }
+
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    pub fn report(&self) -> HealthReport {
+        HealthReport::new(self.servers.iter().map(|server| server.health.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_health::HealthChecks;
+
+    fn link_up(_name: &&'static str) -> Result<(), String> {
+        Err(String::from("no interface up"))
+    }
+
+    /// Every server but net passes, "stuck" never answers
+    struct Fake(&'static str);
+
+    impl Transport for Fake {
+        fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+            match self.0 {
+                "stuck" => None,
+                "net" => HealthChecks::new().readiness("link", link_up).serve(&self.0, request),
+                _ => HealthChecks::<&'static str>::new().serve(&self.0, request),
+            }
+        }
+    }
+
+    fn connect(name: &str) -> Fake {
+        Fake(match name {
+            "fs" => "fs",
+            "net" => "net",
+            "stuck" => "stuck",
+            _ => "other",
+        })
+    }
+
+    #[test]
+    fn test_aggregates_the_servers_checks() {
+        let mut list = Watchlist::new(connect);
+        assert_eq!(list.report().state, HealthState::NotReady);
+        list.poll();
+        let report = list.report();
+        assert_eq!(report.state, HealthState::NotReady);
+        let states: Vec<_> = report.servers.iter().map(|server| server.state).collect();
+        assert_eq!(states, [HealthState::Healthy, HealthState::NotReady, HealthState::Healthy]);
+        assert_eq!(report.servers[1].checks[0].detail, "no interface up");
+
+        list.register("stuck").unwrap();
+        list.register("stuck").unwrap();
+        list.poll();
+        list.poll();
+        let report = list.report();
+        assert_eq!(report.servers.len(), 4);
+        assert_eq!(report.state, HealthState::Unhealthy);
+        assert_eq!(report.servers[3].failures, 2);
+        assert_eq!(report.servers[3].checks[0].name, "answer");
+
+        assert_eq!(list.unregister("fs"), Err(STATUS_EPERM));
+        assert_eq!(list.unregister("stuck"), Ok(()));
+        assert_eq!(list.unregister("stuck"), Err(STATUS_ENOENT));
+        assert_eq!(list.polls(), 3);
+    }
+}
@@ -0,0 +1,610 @@
+/*
+ * Orion Operating System - Identity Accounts
+ *
+ * Users and groups. A user with a password keeps a credential: the salt
+ * and cost of its Argon2id hash, a verifier derived from the hash and the
+ * user's file encryption secret, wrapped with a key derived from the same
+ * hash. Neither the password nor the hash itself is kept, so the secret
+ * is only ever available while its owner logs in. Changing a password
+ * rewraps the secret; an administrator resetting one cannot unwrap it and
+ * gives the user a new one, and the files encrypted under the old one
+ * stay locked.
+ *
+ * Groups carry the capabilities of their members: each grant names a
+ * capability the identity server holds and the rights its members get
+ * on it when they log in.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::argon2::{argon2id, Block, Params};
+use orion_crypto::kdf::hkdf_sha512;
+use orion_crypto::wipe;
+
+pub const SALT_SIZE: usize = 16;
+pub const SECRET_SIZE: usize = 32;
+const VERIFIER_SIZE: usize = 32;
+const HASH_SIZE: usize = 64;
+
+/// Cost of new password hashes (the RFC 9106 second recommendation with
+/// the memory of the OWASP minimum)
+pub const DEFAULT_PARAMS: Params = Params { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 };
+
+/// Largest memory cost the server will run, which bounds its working memory
+pub const MAX_MEMORY_KIB: u32 = 32 * 1024;
+
+pub const MAX_USERS: usize = 1024;
+pub const MAX_GROUPS: usize = 256;
+pub const MAX_GRANTS: usize = 32;
+pub const MAX_NAME: usize = 32;
+pub const MAX_PATH: usize = 256;
+pub const MAX_PASSWORD: usize = 1024;
+
+/// Identifies the database encoding
+const DATABASE_MAGIC: &[u8; 4] = b"OIDB";
+const DATABASE_VERSION: u32 = 1;
+
+// Contexts of the keys derived from a password hash
+const VERIFY_CONTEXT: &[u8] = b"orion-identity verify";
+const WRAP_CONTEXT: &[u8] = b"orion-identity wrap";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountError {
+    NotFound,
+    Exists,
+    InvalidName,
+    InvalidArgument,
+    Full,
+    /// Wrong password, or an account without one
+    Denied,
+    Locked,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Credential {
+    salt: [u8; SALT_SIZE],
+    params: Params,
+    verifier: [u8; VERIFIER_SIZE],
+    wrapped: [u8; SECRET_SIZE],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub uid: u32,
+    pub name: String,
+    pub home: String,
+    pub shell: String,
+    pub locked: bool,
+    credential: Option<Credential>,
+}
+
+impl User {
+    pub fn has_password(&self) -> bool {
+        self.credential.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grant {
+    pub capability: u64,
+    pub rights: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub gid: u32,
+    pub name: String,
+    pub members: Vec<u32>,
+    pub grants: Vec<Grant>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Accounts {
+    users: Vec<User>,
+    groups: Vec<Group>,
+}
+
+/// Lower case letters, digits, '_' and '-', not starting with a digit or '-'
+pub fn valid_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= MAX_NAME
+        && (bytes[0].is_ascii_lowercase() || bytes[0] == b'_')
+        && bytes.iter().all(|&byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_' || byte == b'-')
+}
+
+fn valid_path(path: &str) -> bool {
+    path.starts_with('/') && path.len() <= MAX_PATH && !path.chars().any(|c| c.is_control())
+}
+
+/// Keys derived from the password hash of a credential: the verifier and
+/// the key wrapping the secret
+fn derive(
+    password: &[u8],
+    salt: &[u8; SALT_SIZE],
+    params: &Params,
+    memory: &mut [Block],
+) -> ([u8; VERIFIER_SIZE], [u8; SECRET_SIZE]) {
+    let mut hash = [0u8; HASH_SIZE];
+    argon2id(password, salt, &[], &[], params, memory, &mut hash);
+    let mut verifier = [0u8; VERIFIER_SIZE];
+    let mut wrap = [0u8; SECRET_SIZE];
+    hkdf_sha512(&[], &hash, VERIFY_CONTEXT, &mut verifier);
+    hkdf_sha512(&[], &hash, WRAP_CONTEXT, &mut wrap);
+    wipe(&mut hash);
+    (verifier, wrap)
+}
+
+fn xor(a: &[u8; SECRET_SIZE], b: &[u8; SECRET_SIZE]) -> [u8; SECRET_SIZE] {
+    core::array::from_fn(|index| a[index] ^ b[index])
+}
+
+impl Credential {
+    fn new(
+        password: &[u8],
+        salt: [u8; SALT_SIZE],
+        params: Params,
+        secret: &[u8; SECRET_SIZE],
+        memory: &mut [Block],
+    ) -> Self {
+        let (verifier, mut wrap) = derive(password, &salt, &params, memory);
+        let wrapped = xor(secret, &wrap);
+        wipe(&mut wrap);
+        Self { salt, params, verifier, wrapped }
+    }
+
+    /// The secret, if `password` is the right one
+    fn unwrap(&self, password: &[u8], memory: &mut [Block]) -> Option<[u8; SECRET_SIZE]> {
+        let (verifier, mut wrap) = derive(password, &self.salt, &self.params, memory);
+        // Compare without exiting on the first difference
+        let difference = verifier.iter().zip(self.verifier.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+        let secret = (difference == 0).then(|| xor(&self.wrapped, &wrap));
+        wipe(&mut wrap);
+        secret
+    }
+}
+
+/// Whether `params` may be used for a new hash or checked with `memory`
+fn usable(params: &Params, memory: &[Block]) -> bool {
+    params.is_valid() && params.memory_kib <= MAX_MEMORY_KIB && params.blocks() <= memory.len()
+}
+
+impl Accounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn users(&self) -> impl Iterator<Item = &User> {
+        self.users.iter()
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &Group> {
+        self.groups.iter()
+    }
+
+    pub fn user(&self, name: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.name == name)
+    }
+
+    pub fn user_by_uid(&self, uid: u32) -> Option<&User> {
+        self.users.iter().find(|user| user.uid == uid)
+    }
+
+    fn user_mut(&mut self, name: &str) -> Result<&mut User, AccountError> {
+        self.users.iter_mut().find(|user| user.name == name).ok_or(AccountError::NotFound)
+    }
+
+    fn group_mut(&mut self, name: &str) -> Result<&mut Group, AccountError> {
+        self.groups.iter_mut().find(|group| group.name == name).ok_or(AccountError::NotFound)
+    }
+
+    /// Add a user without a password; it can only log in with a key until
+    /// it is given one
+    pub fn add_user(&mut self, uid: u32, name: &str, home: &str, shell: &str) -> Result<(), AccountError> {
+        if !valid_name(name) {
+            return Err(AccountError::InvalidName);
+        }
+        if !valid_path(home) || !valid_path(shell) {
+            return Err(AccountError::InvalidArgument);
+        }
+        if self.users.iter().any(|user| user.uid == uid || user.name == name) {
+            return Err(AccountError::Exists);
+        }
+        if self.users.len() >= MAX_USERS {
+            return Err(AccountError::Full);
+        }
+        self.users.push(User {
+            uid,
+            name: String::from(name),
+            home: String::from(home),
+            shell: String::from(shell),
+            locked: false,
+            credential: None,
+        });
+        Ok(())
+    }
+
+    /// Remove a user and its group memberships
+    pub fn remove_user(&mut self, name: &str) -> Result<u32, AccountError> {
+        let index = self.users.iter().position(|user| user.name == name).ok_or(AccountError::NotFound)?;
+        let user = self.users.remove(index);
+        for group in self.groups.iter_mut() {
+            group.members.retain(|&uid| uid != user.uid);
+        }
+        Ok(user.uid)
+    }
+
+    pub fn set_locked(&mut self, name: &str, locked: bool) -> Result<(), AccountError> {
+        self.user_mut(name)?.locked = locked;
+        Ok(())
+    }
+
+    /// Set the password of `name` as an administrator, with a new secret
+    /// (drawn by the caller, like the salt)
+    pub fn set_password(
+        &mut self,
+        name: &str,
+        password: &[u8],
+        salt: [u8; SALT_SIZE],
+        secret: &[u8; SECRET_SIZE],
+        params: Params,
+        memory: &mut [Block],
+    ) -> Result<(), AccountError> {
+        if password.is_empty() || password.len() > MAX_PASSWORD || !usable(&params, memory) {
+            return Err(AccountError::InvalidArgument);
+        }
+        let user = self.user_mut(name)?;
+        user.credential = Some(Credential::new(password, salt, params, secret, memory));
+        Ok(())
+    }
+
+    /// Check the password of `name`; its uid and secret if it is right.
+    /// Unknown users cost a hash as well, so the time taken does not tell
+    /// which names exist
+    pub fn verify(
+        &self,
+        name: &str,
+        password: &[u8],
+        memory: &mut [Block],
+    ) -> Result<(u32, [u8; SECRET_SIZE]), AccountError> {
+        let user = self.user(name);
+        let credential = user.and_then(|user| user.credential.as_ref());
+        let Some(credential) = credential.filter(|credential| usable(&credential.params, memory)) else {
+            if DEFAULT_PARAMS.blocks() <= memory.len() {
+                let _ = derive(password, &[0; SALT_SIZE], &DEFAULT_PARAMS, memory);
+            }
+            return Err(if user.is_some() { AccountError::Denied } else { AccountError::NotFound });
+        };
+        let secret = credential.unwrap(password, memory).ok_or(AccountError::Denied)?;
+        let user = user.unwrap();
+        if user.locked {
+            return Err(AccountError::Locked);
+        }
+        Ok((user.uid, secret))
+    }
+
+    /// Change the password of `uid` from `old` to `new`, keeping its secret
+    pub fn change_password(
+        &mut self,
+        uid: u32,
+        old: &[u8],
+        new: &[u8],
+        salt: [u8; SALT_SIZE],
+        params: Params,
+        memory: &mut [Block],
+    ) -> Result<(), AccountError> {
+        if new.is_empty() || new.len() > MAX_PASSWORD || !usable(&params, memory) {
+            return Err(AccountError::InvalidArgument);
+        }
+        let user = self.users.iter_mut().find(|user| user.uid == uid).ok_or(AccountError::NotFound)?;
+        let credential = user.credential.as_ref().ok_or(AccountError::Denied)?;
+        let mut secret = credential.unwrap(old, memory).ok_or(AccountError::Denied)?;
+        user.credential = Some(Credential::new(new, salt, params, &secret, memory));
+        wipe(&mut secret);
+        Ok(())
+    }
+
+    pub fn add_group(&mut self, gid: u32, name: &str) -> Result<(), AccountError> {
+        if !valid_name(name) {
+            return Err(AccountError::InvalidName);
+        }
+        if self.groups.iter().any(|group| group.gid == gid || group.name == name) {
+            return Err(AccountError::Exists);
+        }
+        if self.groups.len() >= MAX_GROUPS {
+            return Err(AccountError::Full);
+        }
+        self.groups.push(Group { gid, name: String::from(name), members: Vec::new(), grants: Vec::new() });
+        Ok(())
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> Result<(), AccountError> {
+        let count = self.groups.len();
+        self.groups.retain(|group| group.name != name);
+        if self.groups.len() == count {
+            return Err(AccountError::NotFound);
+        }
+        Ok(())
+    }
+
+    pub fn add_member(&mut self, group: &str, user: &str) -> Result<(), AccountError> {
+        let uid = self.user(user).ok_or(AccountError::NotFound)?.uid;
+        let group = self.group_mut(group)?;
+        if group.members.contains(&uid) {
+            return Err(AccountError::Exists);
+        }
+        group.members.push(uid);
+        Ok(())
+    }
+
+    pub fn remove_member(&mut self, group: &str, user: &str) -> Result<(), AccountError> {
+        let uid = self.user(user).ok_or(AccountError::NotFound)?.uid;
+        let group = self.group_mut(group)?;
+        let count = group.members.len();
+        group.members.retain(|&member| member != uid);
+        if group.members.len() == count {
+            return Err(AccountError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Give the members of `group` `rights` on `capability`, on top of the
+    /// rights they already get on it
+    pub fn add_grant(&mut self, group: &str, capability: u64, rights: u64) -> Result<(), AccountError> {
+        if rights == 0 {
+            return Err(AccountError::InvalidArgument);
+        }
+        let group = self.group_mut(group)?;
+        if let Some(grant) = group.grants.iter_mut().find(|grant| grant.capability == capability) {
+            grant.rights |= rights;
+            return Ok(());
+        }
+        if group.grants.len() >= MAX_GRANTS {
+            return Err(AccountError::Full);
+        }
+        group.grants.push(Grant { capability, rights });
+        Ok(())
+    }
+
+    pub fn remove_grant(&mut self, group: &str, capability: u64) -> Result<(), AccountError> {
+        let group = self.group_mut(group)?;
+        let count = group.grants.len();
+        group.grants.retain(|grant| grant.capability != capability);
+        if group.grants.len() == count {
+            return Err(AccountError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Groups `uid` belongs to
+    pub fn groups_of(&self, uid: u32) -> impl Iterator<Item = &Group> {
+        self.groups.iter().filter(move |group| group.members.contains(&uid))
+    }
+
+    /// Everything the groups of `uid` grant, one entry per capability
+    pub fn grants_of(&self, uid: u32) -> Vec<Grant> {
+        let mut grants: Vec<Grant> = Vec::new();
+        for grant in self.groups_of(uid).flat_map(|group| group.grants.iter()) {
+            match grants.iter_mut().find(|entry| entry.capability == grant.capability) {
+                Some(entry) => entry.rights |= grant.rights,
+                None => grants.push(*grant),
+            }
+        }
+        grants
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = DATABASE_MAGIC.to_vec();
+        out.extend_from_slice(&DATABASE_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.users.len() as u32).to_le_bytes());
+        for user in self.users.iter() {
+            out.extend_from_slice(&user.uid.to_le_bytes());
+            out.push(user.locked as u8);
+            for text in [&user.name, &user.home, &user.shell] {
+                put_string(&mut out, text);
+            }
+            match &user.credential {
+                Some(credential) => {
+                    out.push(1);
+                    out.extend_from_slice(&credential.salt);
+                    for value in
+                        [credential.params.memory_kib, credential.params.iterations, credential.params.parallelism]
+                    {
+                        out.extend_from_slice(&value.to_le_bytes());
+                    }
+                    out.extend_from_slice(&credential.verifier);
+                    out.extend_from_slice(&credential.wrapped);
+                }
+                None => out.push(0),
+            }
+        }
+        out.extend_from_slice(&(self.groups.len() as u32).to_le_bytes());
+        for group in self.groups.iter() {
+            out.extend_from_slice(&group.gid.to_le_bytes());
+            put_string(&mut out, &group.name);
+            out.extend_from_slice(&(group.members.len() as u32).to_le_bytes());
+            for uid in group.members.iter() {
+                out.extend_from_slice(&uid.to_le_bytes());
+            }
+            out.extend_from_slice(&(group.grants.len() as u32).to_le_bytes());
+            for grant in group.grants.iter() {
+                out.extend_from_slice(&grant.capability.to_le_bytes());
+                out.extend_from_slice(&grant.rights.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data, offset: 0 };
+        if reader.take(4)? != DATABASE_MAGIC || reader.u32()? != DATABASE_VERSION {
+            return None;
+        }
+        let mut accounts = Self::new();
+        for _ in 0..reader.u32()? {
+            let uid = reader.u32()?;
+            let locked = reader.take(1)?[0] != 0;
+            let (name, home, shell) = (reader.string()?, reader.string()?, reader.string()?);
+            let credential = match reader.take(1)?[0] {
+                0 => None,
+                _ => Some(Credential {
+                    salt: reader.take(SALT_SIZE)?.try_into().ok()?,
+                    params: Params { memory_kib: reader.u32()?, iterations: reader.u32()?, parallelism: reader.u32()? },
+                    verifier: reader.take(VERIFIER_SIZE)?.try_into().ok()?,
+                    wrapped: reader.take(SECRET_SIZE)?.try_into().ok()?,
+                }),
+            };
+            accounts.add_user(uid, &name, &home, &shell).ok()?;
+            let user = accounts.users.last_mut()?;
+            user.locked = locked;
+            user.credential = credential;
+        }
+        for _ in 0..reader.u32()? {
+            let gid = reader.u32()?;
+            let name = reader.string()?;
+            accounts.add_group(gid, &name).ok()?;
+            let mut members = Vec::new();
+            for _ in 0..reader.u32()? {
+                members.push(reader.u32()?);
+            }
+            let mut grants = Vec::new();
+            for _ in 0..reader.u32()?.min(MAX_GRANTS as u32 + 1) {
+                grants.push(Grant { capability: reader.u64()?, rights: reader.u64()? });
+            }
+            if grants.len() > MAX_GRANTS {
+                return None;
+            }
+            let group = accounts.groups.last_mut()?;
+            group.members = members;
+            group.grants = grants;
+        }
+        (reader.offset == data.len()).then_some(accounts)
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(length)?)?;
+        self.offset += length;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.u32()? as usize;
+        Some(String::from(core::str::from_utf8(self.take(length)?).ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const CHEAP: Params = Params { memory_kib: 8, iterations: 1, parallelism: 1 };
+
+    fn memory() -> Vec<Block> {
+        vec![[0u64; 128]; 8]
+    }
+
+    #[test]
+    fn test_verifies_passwords_and_unwraps_the_secret() {
+        let mut memory = memory();
+        let mut accounts = Accounts::new();
+        accounts.add_user(1000, "alice", "/home/alice", "/bin/osh").unwrap();
+        assert_eq!(accounts.add_user(1000, "bob", "/home/bob", "/bin/osh"), Err(AccountError::Exists));
+        assert_eq!(accounts.add_user(1001, "Bob", "/home/bob", "/bin/osh"), Err(AccountError::InvalidName));
+
+        // Without a password nothing verifies
+        assert_eq!(accounts.verify("alice", b"", &mut memory), Err(AccountError::Denied));
+        let secret = [0x5a; SECRET_SIZE];
+        accounts.set_password("alice", b"hunter2", [1; SALT_SIZE], &secret, CHEAP, &mut memory).unwrap();
+        assert_eq!(accounts.verify("alice", b"hunter2", &mut memory), Ok((1000, secret)));
+        assert_eq!(accounts.verify("alice", b"hunter3", &mut memory), Err(AccountError::Denied));
+        assert_eq!(accounts.verify("mallory", b"hunter2", &mut memory), Err(AccountError::NotFound));
+
+        // A change keeps the secret, under the new password only
+        assert_eq!(
+            accounts.change_password(1000, b"wrong", b"new", [2; SALT_SIZE], CHEAP, &mut memory),
+            Err(AccountError::Denied)
+        );
+        accounts.change_password(1000, b"hunter2", b"correct horse", [2; SALT_SIZE], CHEAP, &mut memory).unwrap();
+        assert_eq!(accounts.verify("alice", b"hunter2", &mut memory), Err(AccountError::Denied));
+        assert_eq!(accounts.verify("alice", b"correct horse", &mut memory), Ok((1000, secret)));
+
+        // The lock only shows to whoever knows the password
+        accounts.set_locked("alice", true).unwrap();
+        assert_eq!(accounts.verify("alice", b"hunter2", &mut memory), Err(AccountError::Denied));
+        assert_eq!(accounts.verify("alice", b"correct horse", &mut memory), Err(AccountError::Locked));
+
+        // Costs the working memory cannot hold are refused
+        let costly = Params { memory_kib: 64, ..CHEAP };
+        assert_eq!(
+            accounts.set_password("alice", b"x", [3; SALT_SIZE], &secret, costly, &mut memory),
+            Err(AccountError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_merges_group_grants_and_round_trips() {
+        let mut memory = memory();
+        let mut accounts = Accounts::new();
+        accounts.add_user(1000, "alice", "/home/alice", "/bin/osh").unwrap();
+        accounts.add_user(1001, "bob", "/home/bob", "/bin/osh").unwrap();
+        accounts.set_password("bob", b"pw", [4; SALT_SIZE], &[7; SECRET_SIZE], CHEAP, &mut memory).unwrap();
+        accounts.add_group(10, "wheel").unwrap();
+        accounts.add_group(20, "net").unwrap();
+        accounts.add_member("wheel", "alice").unwrap();
+        accounts.add_member("net", "alice").unwrap();
+        accounts.add_member("net", "bob").unwrap();
+        assert_eq!(accounts.add_member("net", "bob"), Err(AccountError::Exists));
+        accounts.add_grant("wheel", 0x100, 1 << 13).unwrap();
+        accounts.add_grant("net", 0x200, 1).unwrap();
+        accounts.add_grant("net", 0x100, 1).unwrap();
+
+        assert_eq!(
+            accounts.grants_of(1000),
+            [Grant { capability: 0x100, rights: (1 << 13) | 1 }, Grant { capability: 0x200, rights: 1 }]
+        );
+        assert_eq!(
+            accounts.grants_of(1001),
+            [Grant { capability: 0x200, rights: 1 }, Grant { capability: 0x100, rights: 1 }]
+        );
+
+        let decoded = Accounts::decode(&accounts.encode()).unwrap();
+        assert_eq!(decoded, accounts);
+        assert_eq!(decoded.verify("bob", b"pw", &mut memory), Ok((1001, [7; SECRET_SIZE])));
+        let mut truncated = accounts.encode();
+        truncated.pop();
+        assert!(Accounts::decode(&truncated).is_none());
+
+        // Removing a user drops its memberships
+        accounts.remove_user("alice").unwrap();
+        assert!(accounts.groups_of(1000).next().is_none());
+        assert!(accounts.grants_of(1000).is_empty());
+    }
+}
@@ -0,0 +1,625 @@
+/*
+ * Orion Operating System - Identity Server
+ *
+ * Users, groups and login sessions. Accounts live in a small database
+ * (see accounts.rs) kept as two files under DATABASE_DIR: each save goes
+ * to the file not holding the latest copy and carries a sequence number
+ * and a SHA-256 of its body, so a save torn by a crash leaves the copy
+ * before it in place. Passwords are hashed with Argon2id in a working
+ * memory locked at startup, like the key arena of the keyring.
+ *
+ * Login front ends (orion-login on the console, the remote shell server
+ * for the network) open sessions and attach the programs they start for
+ * the user, which receive the capabilities of the user's groups (see
+ * sessions.rs), and the MAC label the policy gives the user, if any (see
+ * capabilities/mac.h). A password login also unlocks the user's file
+ * encryption key: the secret unwrapped with the password goes to the
+ * keyring as a symmetric key, and the key derived from it for "fscrypt"
+ * to the fs server, where it opens the user's encrypted directories
+ * until the last session of the user ends. Programs of a password
+ * session get a USE grant on the keyring key, to derive keys of their
+ * own from it.
+ *
+ * Logins, failed logins, logouts and account changes are audited.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_crypto::argon2::{Block, BLOCK_WORDS};
+use orion_crypto::sha256::{Sha256, SHA256_DIGEST_SIZE};
+use orion_crypto::wipe;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_mac::SUBJECT_USER;
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+use orion_sys::{
+    audit_emit, clock_get, close, mac_relabel, madvise, open, read, write, MADV_LOCK, O_CREAT, O_RDONLY, O_TRUNC,
+    O_WRONLY,
+};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the accounts and sessions
+const HEAP_SIZE: usize = 4 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod accounts;
+mod protocol;
+mod sessions;
+
+use accounts::{AccountError, Accounts, DEFAULT_PARAMS, MAX_MEMORY_KIB, SALT_SIZE, SECRET_SIZE};
+use protocol::*;
+use sessions::{Sessions, Throttle};
+
+const BLOCK_COUNT: usize = MAX_MEMORY_KIB as usize;
+
+/// Argon2 working memory, locked at startup
+static mut HASH_MEMORY: [Block; BLOCK_COUNT] = [[0; BLOCK_WORDS]; BLOCK_COUNT];
+
+/// Where the account database is kept, as DATABASE_FILES[sequence % 2]
+const DATABASE_DIR: &str = "/etc/orion/identity";
+const DATABASE_FILES: [&str; 2] = ["accounts.0", "accounts.1"];
+const RECORD_MAGIC: &[u8; 4] = b"OIDS";
+const RECORD_HEADER_SIZE: usize = 12 + SHA256_DIGEST_SIZE;
+
+/// Only the kernel may report process exits
+const KERNEL_ENDPOINT: u64 = 0;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+// Audit record types
+const AUDIT_LOGIN: u32 = 0x1801;
+const AUDIT_LOGIN_FAILED: u32 = 0x1802;
+const AUDIT_LOGOUT: u32 = 0x1803;
+const AUDIT_ACCOUNT_CHANGE: u32 = 0x1804;
+
+/// Entropy GET_RANDOM request opcode (see services/entropy/src/protocol.rs)
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+
+// Keyring requests (see services/keyring/src/protocol.rs)
+const KEYRING_OP_ADD_KEY: u32 = 1;
+const KEYRING_OP_GRANT: u32 = 4;
+const KEYRING_OP_REVOKE: u32 = 5;
+const KEYRING_OP_DERIVE: u32 = 11;
+const KEYRING_KEY_SYMMETRIC: u32 = 4;
+const KEYRING_PERM_USE: u32 = 0x04;
+
+// File encryption key requests of the fs server (see services/fs/src/crypt.rs)
+const FS_OP_ADD_KEY: u32 = 0x4D;
+const FS_OP_REMOVE_KEY: u32 = 0x4E;
+
+/// Purpose of the keyring derivation giving the fs server's key
+const FSCRYPT_PURPOSE: &[u8] = b"fscrypt";
+const FSCRYPT_KEY_SIZE: usize = 64;
+
+/// Session id generation gives up after this many collisions
+const SESSION_ID_ATTEMPTS: usize = 4;
+
+/// File encryption key of a user with a password session open
+struct FileKey {
+    uid: u32,
+    /// Keyring handle of the user's secret
+    handle: u64,
+    identifier: [u8; KEY_IDENTIFIER_SIZE],
+}
+
+struct IdentityServer {
+    accounts: Accounts,
+    sessions: Sessions,
+    throttle: Throttle,
+    file_keys: Vec<FileKey>,
+    memory: &'static mut [Block],
+    /// Sequence of the latest saved database
+    sequence: u64,
+    entropy: IpcChannel,
+    keyring: IpcChannel,
+    fs: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+/// Send a request and return the reply payload of a successful call
+fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = channel.call(request).map_err(|_| STATUS_EIO)?;
+    if response.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn database_path(sequence: u64) -> String {
+    format!("{}/{}", DATABASE_DIR, DATABASE_FILES[(sequence % 2) as usize])
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, i32> {
+    let fd = open(path, O_RDONLY)?;
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let result = loop {
+        match read(fd, &mut chunk) {
+            Ok(0) => break Ok(data),
+            Ok(count) => data.extend_from_slice(&chunk[..count]),
+            Err(status) => break Err(status),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+fn write_file(path: &str, data: &[u8]) -> Result<(), i32> {
+    let fd = open(path, O_WRONLY | O_CREAT | O_TRUNC)?;
+    let mut written = 0;
+    let result = loop {
+        if written == data.len() {
+            break Ok(());
+        }
+        match write(fd, &data[written..]) {
+            Ok(0) => break Err(STATUS_EIO),
+            Ok(count) => written += count,
+            Err(status) => break Err(status),
+        }
+    };
+    let _ = close(fd);
+    result
+}
+
+/// Sequence and body of an intact database record
+fn decode_record(data: &[u8]) -> Option<(u64, &[u8])> {
+    if data.len() < RECORD_HEADER_SIZE || &data[..4] != RECORD_MAGIC {
+        return None;
+    }
+    let sequence = u64::from_le_bytes(data[4..12].try_into().ok()?);
+    let body = &data[RECORD_HEADER_SIZE..];
+    (Sha256::digest(body)[..] == data[12..RECORD_HEADER_SIZE]).then_some((sequence, body))
+}
+
+fn encode_record(sequence: u64, body: &[u8]) -> Vec<u8> {
+    let mut record = RECORD_MAGIC.to_vec();
+    record.extend_from_slice(&sequence.to_le_bytes());
+    record.extend_from_slice(&Sha256::digest(body));
+    record.extend_from_slice(body);
+    record
+}
+
+impl IdentityServer {
+    fn new() -> Self {
+        let memory = unsafe { &mut *core::ptr::addr_of_mut!(HASH_MEMORY) };
+
+        // Password hashes pass through here; keep them out of swap
+        if madvise(memory.as_ptr() as u64, core::mem::size_of_val(memory), MADV_LOCK).is_err() {
+            panic!("identity: unable to lock hash memory");
+        }
+
+        let (sequence, accounts) = Self::load();
+        Self {
+            accounts,
+            sessions: Sessions::new(),
+            throttle: Throttle::new(),
+            file_keys: Vec::new(),
+            memory,
+            sequence,
+            entropy: IpcChannel::connect("entropy"),
+            keyring: IpcChannel::connect("keyring"),
+            fs: IpcChannel::connect("fs"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    /// The latest intact database, or none on a fresh system
+    fn load() -> (u64, Accounts) {
+        let mut latest: Option<(u64, Accounts)> = None;
+        for slot in 0..2 {
+            let Ok(data) = read_file(&database_path(slot)) else {
+                continue;
+            };
+            let Some((sequence, body)) = decode_record(&data) else {
+                continue;
+            };
+            if latest.as_ref().is_some_and(|(newest, _)| *newest >= sequence) {
+                continue;
+            }
+            if let Some(accounts) = Accounts::decode(body) {
+                latest = Some((sequence, accounts));
+            }
+        }
+        latest.unwrap_or_default()
+    }
+
+    /// Persist the accounts; a change that could not be saved holds
+    /// until the server restarts
+    fn save(&mut self) -> Result<(), i32> {
+        let sequence = self.sequence + 1;
+        let mut body = self.accounts.encode();
+        let mut record = encode_record(sequence, &body);
+        let result = write_file(&database_path(sequence), &record);
+        wipe(&mut body);
+        wipe(&mut record);
+        result.map_err(|_| STATUS_EIO)?;
+        self.sequence = sequence;
+        Ok(())
+    }
+
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    fn handle_message(&mut self, mut message: IpcMessage) {
+        let request = IdentityRequest::decode(&message.data);
+        // Requests may carry passwords; do not leave them in the heap
+        wipe(&mut message.data);
+
+        let Some(request) = request else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+            return;
+        };
+
+        if let IdentityRequest::ProcessExit { pid } = request {
+            if message.sender == KERNEL_ENDPOINT {
+                self.sessions.process_exit(pid);
+            }
+            return;
+        }
+
+        let rights = if request.is_public() { CAP_READ } else { CAP_ADMIN };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let status = match self.serve(message.sender, &request, &mut payload) {
+            Ok(()) => STATUS_OK,
+            Err(status) => status,
+        };
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+
+    fn serve(&mut self, sender: u64, request: &IdentityRequest, out: &mut Vec<u8>) -> Result<(), i32> {
+        match request {
+            IdentityRequest::Login { name, password, source } => return self.login(name, password, source, out),
+            IdentityRequest::OpenSession { name, source } => return self.open_session(name, source, out),
+            IdentityRequest::Attach { session, pid } => return self.attach(*session, *pid),
+            IdentityRequest::Logout { session } => {
+                let closed = self.sessions.close(*session).ok_or(STATUS_ENOENT)?;
+                if self.sessions.count_of(closed.uid) == 0 {
+                    self.lock_files(closed.uid);
+                }
+                let record = format!("logout session={:x} user=\"{}\"", closed.id, closed.name);
+                let _ = audit_emit(AUDIT_LOGOUT, record.as_bytes());
+                return Ok(());
+            }
+            IdentityRequest::ListSessions => {
+                for session in self.sessions.iter() {
+                    encode_session(session, out);
+                }
+                return Ok(());
+            }
+            IdentityRequest::Whoami => {
+                let session = self.sessions.of_process(sender).ok_or(STATUS_EPERM)?;
+                out.extend_from_slice(&session.id.to_le_bytes());
+                out.extend_from_slice(&session.uid.to_le_bytes());
+                write_string(&session.name, out);
+                return Ok(());
+            }
+            IdentityRequest::LookupUser { name } => {
+                let user = self.accounts.user(name).ok_or(STATUS_ENOENT)?;
+                let gids: Vec<u32> = self.accounts.groups_of(user.uid).map(|group| group.gid).collect();
+                encode_user(user, &gids, out);
+                return Ok(());
+            }
+            IdentityRequest::ChangePassword { old, new } => return self.change_password(sender, old, new),
+            _ => {}
+        }
+
+        // What is left changes the accounts
+        let accounts = &mut self.accounts;
+        let (result, subject) = match request {
+            IdentityRequest::AddUser { uid, name, home, shell } => (accounts.add_user(*uid, name, home, shell), name),
+            IdentityRequest::RemoveUser { name } => (accounts.remove_user(name).map(|_| ()), name),
+            IdentityRequest::SetPassword { name, password } => {
+                let mut salt = [0u8; SALT_SIZE];
+                let mut secret = [0u8; SECRET_SIZE];
+                random(&mut self.entropy, &mut salt)?;
+                random(&mut self.entropy, &mut secret)?;
+                let result = accounts.set_password(name, password, salt, &secret, DEFAULT_PARAMS, self.memory);
+                wipe(&mut secret);
+                (result, name)
+            }
+            IdentityRequest::LockUser { name, locked } => (accounts.set_locked(name, *locked), name),
+            IdentityRequest::AddGroup { gid, name } => (accounts.add_group(*gid, name), name),
+            IdentityRequest::RemoveGroup { name } => (accounts.remove_group(name), name),
+            IdentityRequest::AddMember { group, user } => (accounts.add_member(group, user), group),
+            IdentityRequest::RemoveMember { group, user } => (accounts.remove_member(group, user), group),
+            IdentityRequest::AddGrant { group, capability, rights } => {
+                (accounts.add_grant(group, *capability, *rights), group)
+            }
+            IdentityRequest::RemoveGrant { group, capability } => (accounts.remove_grant(group, *capability), group),
+            _ => return Err(STATUS_EINVAL),
+        };
+        result.map_err(account_error_status)?;
+
+        let record = format!("account-change op={} name=\"{}\" by={}", request.opcode(), subject, sender);
+        let _ = audit_emit(AUDIT_ACCOUNT_CHANGE, record.as_bytes());
+        self.save()
+    }
+
+    fn login(&mut self, name: &str, password: &[u8], source: &str, out: &mut Vec<u8>) -> Result<(), i32> {
+        let now = monotonic_ns();
+        if !self.throttle.allows(name, now) {
+            self.audit_failure(name, source, "throttled");
+            return Err(STATUS_EAGAIN);
+        }
+        let (uid, mut secret) = match self.accounts.verify(name, password, self.memory) {
+            Ok(verified) => verified,
+            Err(AccountError::Locked) => {
+                self.audit_failure(name, source, "locked");
+                return Err(STATUS_EACCES);
+            }
+            Err(_) => {
+                self.throttle.failed(name, now);
+                self.audit_failure(name, source, "password");
+                return Err(STATUS_EACCES);
+            }
+        };
+        self.throttle.succeeded(name);
+
+        let opened = self.new_session_id().and_then(|id| {
+            self.sessions.open(id, uid, name, source, now, true).map_err(session_error_status)?;
+            Ok(id)
+        });
+        let identifier = match opened {
+            Ok(_) => self.unlock_files(uid, name, &secret),
+            Err(_) => None,
+        };
+        wipe(&mut secret);
+        let id = opened?;
+
+        let user = self.accounts.user_by_uid(uid).ok_or(STATUS_ENOENT)?;
+        encode_login(id, user, &identifier.unwrap_or([0; KEY_IDENTIFIER_SIZE]), out);
+        let record = format!("login session={:x} user=\"{}\" source=\"{}\" password=1", id, name, source);
+        let _ = audit_emit(AUDIT_LOGIN, record.as_bytes());
+        Ok(())
+    }
+
+    fn open_session(&mut self, name: &str, source: &str, out: &mut Vec<u8>) -> Result<(), i32> {
+        let (uid, locked) = self.accounts.user(name).map(|user| (user.uid, user.locked)).ok_or(STATUS_EACCES)?;
+        if locked {
+            self.audit_failure(name, source, "locked");
+            return Err(STATUS_EACCES);
+        }
+        let id = self.new_session_id()?;
+        self.sessions.open(id, uid, name, source, monotonic_ns(), false).map_err(session_error_status)?;
+
+        let user = self.accounts.user_by_uid(uid).ok_or(STATUS_ENOENT)?;
+        encode_login(id, user, &[0; KEY_IDENTIFIER_SIZE], out);
+        let record = format!("login session={:x} user=\"{}\" source=\"{}\" password=0", id, name, source);
+        let _ = audit_emit(AUDIT_LOGIN, record.as_bytes());
+        Ok(())
+    }
+
+    /// Grant the suspended program `pid` what the session's user gets
+    fn attach(&mut self, session: u64, pid: u64) -> Result<(), i32> {
+        let (uid, password, name) = {
+            let session = self.sessions.attach(session, pid).map_err(session_error_status)?;
+            (session.uid, session.password, session.name.clone())
+        };
+        match mac_relabel(pid, SUBJECT_USER, &name) {
+            // No policy label for the user: the program keeps the front end's
+            Ok(()) | Err(STATUS_ENOENT) => {}
+            Err(status) => {
+                self.sessions.process_exit(pid);
+                return Err(status);
+            }
+        }
+        let granted = self
+            .accounts
+            .grants_of(uid)
+            .iter()
+            .all(|grant| self.capabilities.grant(grant.capability, pid, grant.rights));
+        if !granted {
+            // The front end kills a program it could not attach
+            self.sessions.process_exit(pid);
+            return Err(STATUS_EPERM);
+        }
+
+        if let Some(key) = self.file_keys.iter().find(|key| key.uid == uid).filter(|_| password) {
+            let mut request = KEYRING_OP_GRANT.to_le_bytes().to_vec();
+            request.extend_from_slice(&key.handle.to_le_bytes());
+            request.extend_from_slice(&pid.to_le_bytes());
+            request.extend_from_slice(&KEYRING_PERM_USE.to_le_bytes());
+            let _ = call(&mut self.keyring, &request);
+        }
+        Ok(())
+    }
+
+    fn change_password(&mut self, sender: u64, old: &[u8], new: &[u8]) -> Result<(), i32> {
+        let (uid, name) = {
+            let session = self.sessions.of_process(sender).ok_or(STATUS_EPERM)?;
+            (session.uid, session.name.clone())
+        };
+        let now = monotonic_ns();
+        if !self.throttle.allows(&name, now) {
+            return Err(STATUS_EAGAIN);
+        }
+        let mut salt = [0u8; SALT_SIZE];
+        random(&mut self.entropy, &mut salt)?;
+        match self.accounts.change_password(uid, old, new, salt, DEFAULT_PARAMS, self.memory) {
+            Ok(()) => self.throttle.succeeded(&name),
+            Err(AccountError::Denied) => {
+                self.throttle.failed(&name, now);
+                return Err(STATUS_EACCES);
+            }
+            Err(error) => return Err(account_error_status(error)),
+        }
+        let record = format!("account-change op={} name=\"{}\" by={}", OP_CHANGE_PASSWORD, name, sender);
+        let _ = audit_emit(AUDIT_ACCOUNT_CHANGE, record.as_bytes());
+        self.save()
+    }
+
+    fn audit_failure(&self, name: &str, source: &str, reason: &str) {
+        let record = format!("login-failed user=\"{}\" source=\"{}\" reason={}", name, source, reason);
+        let _ = audit_emit(AUDIT_LOGIN_FAILED, record.as_bytes());
+    }
+
+    /// Hand the file encryption key of `uid` to the fs server, unless a
+    /// session of the user already did; its identifier
+    fn unlock_files(&mut self, uid: u32, name: &str, secret: &[u8; SECRET_SIZE]) -> Option<[u8; KEY_IDENTIFIER_SIZE]> {
+        if let Some(key) = self.file_keys.iter().find(|key| key.uid == uid) {
+            return Some(key.identifier);
+        }
+
+        let description = format!("fscrypt:{}", name);
+        let mut request = KEYRING_OP_ADD_KEY.to_le_bytes().to_vec();
+        request.extend_from_slice(&KEYRING_KEY_SYMMETRIC.to_le_bytes());
+        request.extend_from_slice(&(description.len() as u32).to_le_bytes());
+        request.extend_from_slice(description.as_bytes());
+        request.extend_from_slice(secret);
+        let added = call(&mut self.keyring, &request);
+        wipe(&mut request);
+        let handle = u64::from_le_bytes(added.ok()?.get(..8)?.try_into().ok()?);
+
+        let mut request = KEYRING_OP_DERIVE.to_le_bytes().to_vec();
+        request.extend_from_slice(&handle.to_le_bytes());
+        request.extend_from_slice(FSCRYPT_PURPOSE);
+        let identifier = match call(&mut self.keyring, &request) {
+            Ok(mut key) if key.len() == FSCRYPT_KEY_SIZE => {
+                let mut request = FS_OP_ADD_KEY.to_le_bytes().to_vec();
+                request.extend_from_slice(&key);
+                wipe(&mut key);
+                let added = call(&mut self.fs, &request);
+                wipe(&mut request);
+                added.ok().and_then(|reply| reply.get(..KEY_IDENTIFIER_SIZE)?.try_into().ok())
+            }
+            Ok(mut key) => {
+                wipe(&mut key);
+                None
+            }
+            Err(_) => None,
+        };
+
+        match identifier {
+            Some(identifier) => {
+                self.file_keys.push(FileKey { uid, handle, identifier });
+                Some(identifier)
+            }
+            None => {
+                let _ = call(&mut self.keyring, &revoke_request(handle));
+                None
+            }
+        }
+    }
+
+    /// Take the file encryption key of `uid` back from the fs server and
+    /// the keyring
+    fn lock_files(&mut self, uid: u32) {
+        let Some(index) = self.file_keys.iter().position(|key| key.uid == uid) else {
+            return;
+        };
+        let key = self.file_keys.remove(index);
+        let mut request = FS_OP_REMOVE_KEY.to_le_bytes().to_vec();
+        request.extend_from_slice(&0u32.to_le_bytes());
+        request.extend_from_slice(&key.identifier);
+        let _ = call(&mut self.fs, &request);
+        let _ = call(&mut self.keyring, &revoke_request(key.handle));
+    }
+
+    /// Draw an unguessable session id from the entropy service
+    fn new_session_id(&mut self) -> Result<u64, i32> {
+        for _ in 0..SESSION_ID_ATTEMPTS {
+            let mut bytes = [0u8; 8];
+            random(&mut self.entropy, &mut bytes)?;
+            let id = u64::from_le_bytes(bytes);
+            if id != 0 && !self.sessions.contains(id) {
+                return Ok(id);
+            }
+        }
+        Err(STATUS_EAGAIN)
+    }
+}
+
+impl IdentityRequest {
+    /// Opcode of an account change, for its audit record
+    fn opcode(&self) -> u32 {
+        match self {
+            IdentityRequest::AddUser { .. } => OP_ADD_USER,
+            IdentityRequest::RemoveUser { .. } => OP_REMOVE_USER,
+            IdentityRequest::SetPassword { .. } => OP_SET_PASSWORD,
+            IdentityRequest::LockUser { .. } => OP_LOCK_USER,
+            IdentityRequest::AddGroup { .. } => OP_ADD_GROUP,
+            IdentityRequest::RemoveGroup { .. } => OP_REMOVE_GROUP,
+            IdentityRequest::AddMember { .. } => OP_ADD_MEMBER,
+            IdentityRequest::RemoveMember { .. } => OP_REMOVE_MEMBER,
+            IdentityRequest::AddGrant { .. } => OP_ADD_GRANT,
+            IdentityRequest::RemoveGrant { .. } => OP_REMOVE_GRANT,
+            _ => 0,
+        }
+    }
+}
+
+fn revoke_request(handle: u64) -> Vec<u8> {
+    let mut request = KEYRING_OP_REVOKE.to_le_bytes().to_vec();
+    request.extend_from_slice(&handle.to_le_bytes());
+    request
+}
+
+/// Fill `out` from the entropy service
+fn random(entropy: &mut IpcChannel, out: &mut [u8]) -> Result<(), i32> {
+    let mut request = Vec::with_capacity(12);
+    request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+    request.extend_from_slice(&(out.len() as u32).to_le_bytes());
+    request.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut bytes = call(entropy, &request)?;
+    if bytes.len() < out.len() {
+        return Err(STATUS_EIO);
+    }
+    out.copy_from_slice(&bytes[..out.len()]);
+    wipe(&mut bytes);
+    Ok(())
+}
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = IdentityServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,352 @@
+/*
+ * Orion Operating System - Identity Server Protocol
+ *
+ * IPC requests of the identity server. All fields are little-endian;
+ * every message starts with a 32-bit opcode and every reply starts with
+ * a 32-bit signed status (0 or a negative errno).
+ *
+ * Administration, with CAP_ADMIN:
+ *
+ *   ADD_USER        uid:u32 name home shell       -> (empty)
+ *   REMOVE_USER     name                          -> (empty)
+ *   SET_PASSWORD    name password                 -> (empty)
+ *   LOCK_USER       locked:u32 name               -> (empty)
+ *   ADD_GROUP       gid:u32 name                  -> (empty)
+ *   REMOVE_GROUP    name                          -> (empty)
+ *   ADD_MEMBER      group user                    -> (empty)
+ *   REMOVE_MEMBER   group user                    -> (empty)
+ *   ADD_GRANT       capability:u64 rights:u64
+ *                   group                         -> (empty)
+ *   REMOVE_GRANT    capability:u64 group          -> (empty)
+ *   LIST_SESSIONS   (none)                        -> { session:u64 uid:u32
+ *                                                     started:u64
+ *                                                     processes:u32
+ *                                                     name source }*
+ *
+ * Login front ends, with CAP_ADMIN:
+ *
+ *   LOGIN           name password source          -> login
+ *   OPEN_SESSION    name source                   -> login
+ *   ATTACH          session:u64 pid:u64           -> (empty)
+ *   LOGOUT          session:u64                   -> (empty)
+ *
+ * where login is `session:u64 uid:u32 key_id[16] home shell`. LOGIN
+ * checks a password; OPEN_SESSION is for front ends that authenticated
+ * the user themselves (the remote shell server checks a key) and only
+ * needs the account to exist and not be locked. A wrong password and an
+ * unknown name both answer EACCES; a name refused after too many
+ * failures answers EAGAIN. `key_id` identifies the user's file
+ * encryption key, added to the fs server for as long as the user has a
+ * password session open; it is zero for OPEN_SESSION and for accounts
+ * without a password. ATTACH puts the suspended program `pid` in the
+ * session and grants it what the user's groups grant (see accounts.rs).
+ *
+ * Any process with CAP_READ:
+ *
+ *   WHOAMI          (none)                        -> session:u64 uid:u32 name
+ *   LOOKUP_USER     name                          -> uid:u32 locked:u32
+ *                                                    password:u32 groups:u32
+ *                                                    { gid:u32 }* home shell
+ *   CHANGE_PASSWORD old new                       -> (empty)
+ *
+ * WHOAMI and CHANGE_PASSWORD are for programs attached to a session,
+ * and change the password of the session's user.
+ *
+ * Strings are a `len: u32` followed by UTF-8 bytes; passwords are a
+ * `len: u32` followed by bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::wipe;
+
+use crate::accounts::{AccountError, User, MAX_PASSWORD};
+use crate::sessions::{Session, SessionError};
+
+// Opcodes
+pub const OP_ADD_USER: u32 = 1;
+pub const OP_REMOVE_USER: u32 = 2;
+pub const OP_SET_PASSWORD: u32 = 3;
+pub const OP_LOCK_USER: u32 = 4;
+pub const OP_ADD_GROUP: u32 = 5;
+pub const OP_REMOVE_GROUP: u32 = 6;
+pub const OP_ADD_MEMBER: u32 = 7;
+pub const OP_REMOVE_MEMBER: u32 = 8;
+pub const OP_ADD_GRANT: u32 = 9;
+pub const OP_REMOVE_GRANT: u32 = 10;
+pub const OP_LIST_SESSIONS: u32 = 11;
+pub const OP_LOGIN: u32 = 0x10;
+pub const OP_OPEN_SESSION: u32 = 0x11;
+pub const OP_ATTACH: u32 = 0x12;
+pub const OP_LOGOUT: u32 = 0x13;
+pub const OP_WHOAMI: u32 = 0x20;
+pub const OP_LOOKUP_USER: u32 = 0x21;
+pub const OP_CHANGE_PASSWORD: u32 = 0x22;
+pub const OP_PROCESS_EXIT: u32 = 0x30;
+
+/// Size of the fs server's key identifiers (see services/fs/src/crypt.rs)
+pub const KEY_IDENTIFIER_SIZE: usize = 16;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EACCES: i32 = -13;
+pub const STATUS_EEXIST: i32 = -17;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IdentityRequest {
+    AddUser { uid: u32, name: String, home: String, shell: String },
+    RemoveUser { name: String },
+    SetPassword { name: String, password: Vec<u8> },
+    LockUser { name: String, locked: bool },
+    AddGroup { gid: u32, name: String },
+    RemoveGroup { name: String },
+    AddMember { group: String, user: String },
+    RemoveMember { group: String, user: String },
+    AddGrant { group: String, capability: u64, rights: u64 },
+    RemoveGrant { group: String, capability: u64 },
+    ListSessions,
+    Login { name: String, password: Vec<u8>, source: String },
+    OpenSession { name: String, source: String },
+    Attach { session: u64, pid: u64 },
+    Logout { session: u64 },
+    Whoami,
+    LookupUser { name: String },
+    ChangePassword { old: Vec<u8>, new: Vec<u8> },
+    ProcessExit { pid: u64 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, bytes` field, returning it with the offset past it
+fn read_bytes(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..(offset + 4).checked_add(len)?)?;
+    Some((bytes, offset + 4 + len))
+}
+
+/// Decode a `len, utf-8 bytes` field, returning it with the offset past it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let (bytes, offset) = read_bytes(data, offset)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset))
+}
+
+fn read_password(data: &[u8], offset: usize) -> Option<(Vec<u8>, usize)> {
+    let (bytes, offset) = read_bytes(data, offset)?;
+    (bytes.len() <= MAX_PASSWORD).then(|| (bytes.to_vec(), offset))
+}
+
+pub fn write_string(text: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+impl IdentityRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let request = match read_u32(data, 0)? {
+            OP_ADD_USER => {
+                let (name, offset) = read_string(data, 8)?;
+                let (home, offset) = read_string(data, offset)?;
+                let (shell, _) = read_string(data, offset)?;
+                IdentityRequest::AddUser { uid: read_u32(data, 4)?, name, home, shell }
+            }
+            OP_REMOVE_USER => IdentityRequest::RemoveUser { name: read_string(data, 4)?.0 },
+            OP_SET_PASSWORD => {
+                let (name, offset) = read_string(data, 4)?;
+                IdentityRequest::SetPassword { name, password: read_password(data, offset)?.0 }
+            }
+            OP_LOCK_USER => {
+                IdentityRequest::LockUser { locked: read_u32(data, 4)? != 0, name: read_string(data, 8)?.0 }
+            }
+            OP_ADD_GROUP => IdentityRequest::AddGroup { gid: read_u32(data, 4)?, name: read_string(data, 8)?.0 },
+            OP_REMOVE_GROUP => IdentityRequest::RemoveGroup { name: read_string(data, 4)?.0 },
+            OP_ADD_MEMBER | OP_REMOVE_MEMBER => {
+                let (group, offset) = read_string(data, 4)?;
+                let (user, _) = read_string(data, offset)?;
+                if read_u32(data, 0)? == OP_ADD_MEMBER {
+                    IdentityRequest::AddMember { group, user }
+                } else {
+                    IdentityRequest::RemoveMember { group, user }
+                }
+            }
+            OP_ADD_GRANT => IdentityRequest::AddGrant {
+                capability: read_u64(data, 4)?,
+                rights: read_u64(data, 12)?,
+                group: read_string(data, 20)?.0,
+            },
+            OP_REMOVE_GRANT => {
+                IdentityRequest::RemoveGrant { capability: read_u64(data, 4)?, group: read_string(data, 12)?.0 }
+            }
+            OP_LIST_SESSIONS => IdentityRequest::ListSessions,
+            OP_LOGIN => {
+                let (name, offset) = read_string(data, 4)?;
+                let (password, offset) = read_password(data, offset)?;
+                let (source, _) = read_string(data, offset)?;
+                IdentityRequest::Login { name, password, source }
+            }
+            OP_OPEN_SESSION => {
+                let (name, offset) = read_string(data, 4)?;
+                let (source, _) = read_string(data, offset)?;
+                IdentityRequest::OpenSession { name, source }
+            }
+            OP_ATTACH => IdentityRequest::Attach { session: read_u64(data, 4)?, pid: read_u64(data, 12)? },
+            OP_LOGOUT => IdentityRequest::Logout { session: read_u64(data, 4)? },
+            OP_WHOAMI => IdentityRequest::Whoami,
+            OP_LOOKUP_USER => IdentityRequest::LookupUser { name: read_string(data, 4)?.0 },
+            OP_CHANGE_PASSWORD => {
+                let (old, offset) = read_password(data, 4)?;
+                let (new, _) = read_password(data, offset)?;
+                IdentityRequest::ChangePassword { old, new }
+            }
+            OP_PROCESS_EXIT => IdentityRequest::ProcessExit { pid: read_u64(data, 4)? },
+            _ => return None,
+        };
+        Some(request)
+    }
+
+    /// Whether the request is served to any process with CAP_READ rather
+    /// than to administrators and login front ends only
+    pub fn is_public(&self) -> bool {
+        matches!(
+            self,
+            IdentityRequest::Whoami | IdentityRequest::LookupUser { .. } | IdentityRequest::ChangePassword { .. }
+        )
+    }
+}
+
+impl Drop for IdentityRequest {
+    fn drop(&mut self) {
+        match self {
+            IdentityRequest::SetPassword { password, .. } | IdentityRequest::Login { password, .. } => wipe(password),
+            IdentityRequest::ChangePassword { old, new } => {
+                wipe(old);
+                wipe(new);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn account_error_status(error: AccountError) -> i32 {
+    match error {
+        AccountError::NotFound => STATUS_ENOENT,
+        AccountError::Exists => STATUS_EEXIST,
+        AccountError::InvalidName | AccountError::InvalidArgument => STATUS_EINVAL,
+        AccountError::Full => STATUS_ENOSPC,
+        AccountError::Denied | AccountError::Locked => STATUS_EACCES,
+    }
+}
+
+pub fn session_error_status(error: SessionError) -> i32 {
+    match error {
+        SessionError::NotFound => STATUS_ENOENT,
+        SessionError::Full => STATUS_ENOSPC,
+        SessionError::Attached => STATUS_EEXIST,
+    }
+}
+
+/// LOGIN and OPEN_SESSION reply
+pub fn encode_login(session: u64, user: &User, key_identifier: &[u8; KEY_IDENTIFIER_SIZE], out: &mut Vec<u8>) {
+    out.extend_from_slice(&session.to_le_bytes());
+    out.extend_from_slice(&user.uid.to_le_bytes());
+    out.extend_from_slice(key_identifier);
+    write_string(&user.home, out);
+    write_string(&user.shell, out);
+}
+
+/// LOOKUP_USER reply
+pub fn encode_user(user: &User, gids: &[u32], out: &mut Vec<u8>) {
+    for value in [user.uid, user.locked as u32, user.has_password() as u32, gids.len() as u32] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    for gid in gids {
+        out.extend_from_slice(&gid.to_le_bytes());
+    }
+    write_string(&user.home, out);
+    write_string(&user.shell, out);
+}
+
+/// Append a LIST_SESSIONS record
+pub fn encode_session(session: &Session, out: &mut Vec<u8>) {
+    out.extend_from_slice(&session.id.to_le_bytes());
+    out.extend_from_slice(&session.uid.to_le_bytes());
+    out.extend_from_slice(&session.started.to_le_bytes());
+    out.extend_from_slice(&(session.pids.len() as u32).to_le_bytes());
+    write_string(&session.name, out);
+    write_string(&session.source, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(bytes);
+    }
+
+    #[test]
+    fn test_decodes_logins_and_administration() {
+        let mut login = OP_LOGIN.to_le_bytes().to_vec();
+        write_string("alice", &mut login);
+        write_bytes(b"hunter2", &mut login);
+        write_string("ttyS0", &mut login);
+        let request = IdentityRequest::decode(&login).unwrap();
+        assert_eq!(
+            request,
+            IdentityRequest::Login {
+                name: String::from("alice"),
+                password: b"hunter2".to_vec(),
+                source: String::from("ttyS0")
+            }
+        );
+        assert!(!request.is_public());
+        assert_eq!(IdentityRequest::decode(&login[..login.len() - 1]), None);
+
+        let mut grant = OP_ADD_GRANT.to_le_bytes().to_vec();
+        grant.extend_from_slice(&0x40u64.to_le_bytes());
+        grant.extend_from_slice(&3u64.to_le_bytes());
+        write_string("wheel", &mut grant);
+        assert_eq!(
+            IdentityRequest::decode(&grant),
+            Some(IdentityRequest::AddGrant { group: String::from("wheel"), capability: 0x40, rights: 3 })
+        );
+
+        let mut member = OP_REMOVE_MEMBER.to_le_bytes().to_vec();
+        write_string("wheel", &mut member);
+        write_string("bob", &mut member);
+        assert_eq!(
+            IdentityRequest::decode(&member),
+            Some(IdentityRequest::RemoveMember { group: String::from("wheel"), user: String::from("bob") })
+        );
+
+        // Passwords past MAX_PASSWORD are not even decoded
+        let mut change = OP_CHANGE_PASSWORD.to_le_bytes().to_vec();
+        write_bytes(b"old", &mut change);
+        write_bytes(&[b'x'; MAX_PASSWORD + 1], &mut change);
+        assert_eq!(IdentityRequest::decode(&change), None);
+        assert!(IdentityRequest::decode(&OP_WHOAMI.to_le_bytes()).unwrap().is_public());
+    }
+}
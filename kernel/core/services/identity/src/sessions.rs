@@ -0,0 +1,275 @@
+/*
+ * Orion Operating System - Identity Sessions
+ *
+ * Login sessions and the processes attached to them. A front end (the
+ * console login, the remote shell server) opens a session once a user
+ * proved who they are, starts the user's programs suspended and attaches
+ * them before resuming them; attaching grants a program the capabilities
+ * of the user's groups. Programs leave their session when they exit; the
+ * session itself lasts until its front end logs it out.
+ *
+ * Failed password logins are throttled per name: after MAX_FAILURES in a
+ * row the name is refused for LOCKOUT_NS, whatever password comes with it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const MAX_SESSIONS: usize = 256;
+pub const MAX_SESSION_PROCESSES: usize = 64;
+
+/// Longest description of where a login came from
+pub const MAX_SOURCE: usize = 64;
+
+/// Failed logins in a row before a name is refused for a while
+pub const MAX_FAILURES: u32 = 5;
+pub const LOCKOUT_NS: u64 = 60_000_000_000;
+
+/// Names whose failures are remembered. A name still refused is never
+/// forgotten to make room; the one with the fewest failures is
+const MAX_TRACKED: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionError {
+    NotFound,
+    Full,
+    /// The process is already in a session
+    Attached,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: u64,
+    pub uid: u32,
+    pub name: String,
+    pub source: String,
+    pub started: u64,
+    /// Opened with a password rather than a key
+    pub password: bool,
+    pub pids: Vec<u64>,
+}
+
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Vec<Session>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter()
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.sessions.iter().any(|session| session.id == id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Session> {
+        self.sessions.iter().find(|session| session.id == id)
+    }
+
+    /// Add a session under `id`, drawn by the caller
+    pub fn open(
+        &mut self,
+        id: u64,
+        uid: u32,
+        name: &str,
+        source: &str,
+        started: u64,
+        password: bool,
+    ) -> Result<(), SessionError> {
+        if self.sessions.len() >= MAX_SESSIONS {
+            return Err(SessionError::Full);
+        }
+        let mut end = source.len().min(MAX_SOURCE);
+        while !source.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.sessions.push(Session {
+            id,
+            uid,
+            name: String::from(name),
+            source: String::from(&source[..end]),
+            started,
+            password,
+            pids: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// End a session; its programs keep running with what they were granted
+    pub fn close(&mut self, id: u64) -> Option<Session> {
+        let index = self.sessions.iter().position(|session| session.id == id)?;
+        Some(self.sessions.remove(index))
+    }
+
+    /// Sessions of `uid` still open
+    pub fn count_of(&self, uid: u32) -> usize {
+        self.sessions.iter().filter(|session| session.uid == uid).count()
+    }
+
+    pub fn attach(&mut self, id: u64, pid: u64) -> Result<&Session, SessionError> {
+        if self.of_process(pid).is_some() {
+            return Err(SessionError::Attached);
+        }
+        let session = self.sessions.iter_mut().find(|session| session.id == id).ok_or(SessionError::NotFound)?;
+        if session.pids.len() >= MAX_SESSION_PROCESSES {
+            return Err(SessionError::Full);
+        }
+        session.pids.push(pid);
+        Ok(session)
+    }
+
+    /// The session the program `pid` runs in
+    pub fn of_process(&self, pid: u64) -> Option<&Session> {
+        self.sessions.iter().find(|session| session.pids.contains(&pid))
+    }
+
+    pub fn process_exit(&mut self, pid: u64) {
+        for session in self.sessions.iter_mut() {
+            session.pids.retain(|&entry| entry != pid);
+        }
+    }
+}
+
+struct Failures {
+    name: String,
+    count: u32,
+    refused_until: u64,
+}
+
+/// Failed password logins by name
+#[derive(Default)]
+pub struct Throttle {
+    entries: Vec<Failures>,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `name` may try a password at `now`
+    pub fn allows(&self, name: &str, now: u64) -> bool {
+        self.entries.iter().find(|entry| entry.name == name).is_none_or(|entry| entry.refused_until <= now)
+    }
+
+    pub fn failed(&mut self, name: &str, now: u64) {
+        let index = match self.entries.iter().position(|entry| entry.name == name) {
+            Some(index) => index,
+            None => {
+                if self.entries.len() >= MAX_TRACKED {
+                    // Flooding other names must not lift a lockout. With
+                    // every tracked name refused, the failure goes uncounted
+                    let Some(victim) = self
+                        .entries
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, entry)| entry.refused_until <= now)
+                        .min_by_key(|(_, entry)| entry.count)
+                        .map(|(index, _)| index)
+                    else {
+                        return;
+                    };
+                    self.entries.remove(victim);
+                }
+                self.entries.push(Failures { name: String::from(name), count: 0, refused_until: 0 });
+                self.entries.len() - 1
+            }
+        };
+        let entry = &mut self.entries[index];
+        entry.count += 1;
+        if entry.count >= MAX_FAILURES {
+            entry.count = 0;
+            entry.refused_until = now + LOCKOUT_NS;
+        }
+    }
+
+    pub fn succeeded(&mut self, name: &str) {
+        self.entries.retain(|entry| entry.name != name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attaches_processes_once() {
+        let mut sessions = Sessions::new();
+        sessions.open(0xa1, 1000, "alice", "ttyS0", 5, true).unwrap();
+        sessions.open(0xb2, 1000, "alice", "rsh 10.0.0.2", 6, false).unwrap();
+        assert_eq!(sessions.count_of(1000), 2);
+
+        assert_eq!(sessions.attach(0xa1, 40).map(|session| session.uid), Ok(1000));
+        assert_eq!(sessions.attach(0xb2, 40).err(), Some(SessionError::Attached));
+        assert_eq!(sessions.attach(0xc3, 41).err(), Some(SessionError::NotFound));
+        assert_eq!(sessions.of_process(40).map(|session| session.id), Some(0xa1));
+
+        sessions.process_exit(40);
+        assert!(sessions.of_process(40).is_none());
+        assert_eq!(sessions.close(0xa1).map(|session| session.source), Some(String::from("ttyS0")));
+        assert!(sessions.close(0xa1).is_none());
+        assert_eq!(sessions.count_of(1000), 1);
+    }
+
+    #[test]
+    fn test_refuses_a_name_after_repeated_failures() {
+        let mut throttle = Throttle::new();
+        for _ in 0..MAX_FAILURES - 1 {
+            throttle.failed("root", 10);
+        }
+        assert!(throttle.allows("root", 10));
+        throttle.failed("root", 10);
+        assert!(!throttle.allows("root", 10 + LOCKOUT_NS - 1));
+        assert!(throttle.allows("alice", 10));
+        assert!(throttle.allows("root", 10 + LOCKOUT_NS));
+
+        // A success clears the count
+        for _ in 0..MAX_FAILURES - 1 {
+            throttle.failed("alice", 20);
+        }
+        throttle.succeeded("alice");
+        throttle.failed("alice", 20);
+        assert!(throttle.allows("alice", 20));
+    }
+
+    #[test]
+    fn test_keeps_a_lockout_through_a_flood_of_names() {
+        let mut throttle = Throttle::new();
+        for _ in 0..MAX_FAILURES {
+            throttle.failed("root", 10);
+        }
+        throttle.failed("alice", 10);
+        throttle.failed("alice", 10);
+        for index in 0..4 * MAX_TRACKED {
+            throttle.failed(&alloc::format!("bogus{}", index), 20);
+        }
+        assert!(!throttle.allows("root", 30));
+        assert_eq!(throttle.entries.len(), MAX_TRACKED);
+        // The name with the most failures outlasted the single failures
+        assert!(throttle.entries.iter().any(|entry| entry.name == "alice"));
+
+        // Once every tracked name is refused, other names go uncounted
+        let mut throttle = Throttle::new();
+        for index in 0..MAX_TRACKED {
+            for _ in 0..MAX_FAILURES {
+                throttle.failed(&alloc::format!("user{}", index), 10);
+            }
+        }
+        throttle.failed("root", 20);
+        assert!(!throttle.entries.iter().any(|entry| entry.name == "root"));
+        assert!(!throttle.allows("user0", 20));
+        throttle.failed("root", 10 + LOCKOUT_NS);
+        assert!(throttle.entries.iter().any(|entry| entry.name == "root"));
+    }
+}
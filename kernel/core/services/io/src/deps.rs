@@ -0,0 +1,322 @@
+/*
+ * Orion Operating System - Driver Dependencies
+ *
+ * Drivers rely on facilities that other drivers or servers bring up: a
+ * GPU driver needs the PCI bus, DMA and interrupts, the NBD driver needs
+ * the network stack. The driver manifest declares them next to the
+ * sandbox settings (see sandbox.rs):
+ *
+ *   requires = pci, dma, irq
+ *   provides = gpu
+ *
+ * A facility is available once whoever brings it up announces it: the
+ * kernel or an administrator with PROVIDE, or a driver listing it under
+ * `provides` once that driver reports itself ready. A load whose
+ * requirements are not all available is deferred, and deferred drivers
+ * are started as their requirements become available, so drivers come up
+ * in dependency order whatever order they were loaded in. A load that
+ * would close a cycle among deferred drivers is refused, naming every
+ * driver on the cycle and what it waits for.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::sandbox::ManifestError;
+
+/// Most facilities one driver may require or provide
+pub const MAX_DEPENDENCIES: usize = 16;
+
+/// Most loads waiting for their requirements at once
+pub const MAX_DEFERRED: usize = 64;
+
+/// Facility names: letters, digits, `-`, `_` and `.`
+pub fn valid_facility(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DriverDeps {
+    pub requires: Vec<String>,
+    pub provides: Vec<String>,
+}
+
+impl DriverDeps {
+    /// Read the `requires` and `provides` lines of a driver manifest; the
+    /// other lines are the sandbox manifest's to check
+    pub fn parse(text: &str) -> Result<Self, ManifestError> {
+        let mut deps = DriverDeps::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let list = match key.trim() {
+                "requires" => &mut deps.requires,
+                "provides" => &mut deps.provides,
+                _ => continue,
+            };
+            for name in value.split(',').map(str::trim) {
+                if !valid_facility(name) {
+                    return Err(ManifestError::Invalid(index + 1));
+                }
+                if !list.iter().any(|known| known == name) {
+                    list.push(String::from(name));
+                }
+            }
+        }
+
+        if deps.requires.len() > MAX_DEPENDENCIES || deps.provides.len() > MAX_DEPENDENCIES {
+            return Err(ManifestError::TooMany);
+        }
+        Ok(deps)
+    }
+}
+
+/// A load waiting for its requirements
+pub struct Deferred<T> {
+    pub name: String,
+    pub deps: DriverDeps,
+    pub load: T,
+}
+
+/// Drivers closing a dependency cycle: each needs the facility beside it
+/// from the next one, the last from the first
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cycle(pub Vec<(String, String)>);
+
+impl Cycle {
+    /// "a needs x from b, b needs y from a"
+    pub fn describe(&self) -> String {
+        let mut text = String::new();
+        for (index, (driver, facility)) in self.0.iter().enumerate() {
+            let next = &self.0[(index + 1) % self.0.len()].0;
+            if index > 0 {
+                text.push_str(", ");
+            }
+            text.push_str(&format!("{} needs {} from {}", driver, facility, next));
+        }
+        text
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeferError {
+    Full,
+    Cycle(Cycle),
+}
+
+pub struct Resolver<T> {
+    available: BTreeSet<String>,
+    deferred: Vec<Deferred<T>>,
+}
+
+impl<T> Default for Resolver<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Resolver<T> {
+    pub fn new() -> Self {
+        Self { available: BTreeSet::new(), deferred: Vec::new() }
+    }
+
+    /// Mark a facility available; false if it already was
+    pub fn provide(&mut self, facility: &str) -> bool {
+        self.available.insert(String::from(facility))
+    }
+
+    /// A facility whose provider went away stops being available; loads
+    /// deferred after that wait for it again
+    pub fn withdraw(&mut self, facility: &str) -> bool {
+        self.available.remove(facility)
+    }
+
+    pub fn is_available(&self, facility: &str) -> bool {
+        self.available.contains(facility)
+    }
+
+    /// Requirements of `deps` nobody has provided yet
+    pub fn missing<'a>(&self, deps: &'a DriverDeps) -> Vec<&'a str> {
+        deps.requires.iter().map(String::as_str).filter(|name| !self.is_available(name)).collect()
+    }
+
+    pub fn deferred(&self) -> impl Iterator<Item = &Deferred<T>> {
+        self.deferred.iter()
+    }
+
+    /// Queue a load until its requirements are available, unless waiting
+    /// would never end because it closes a cycle
+    pub fn defer(&mut self, name: &str, deps: DriverDeps, load: T) -> Result<(), DeferError> {
+        if self.deferred.len() >= MAX_DEFERRED {
+            return Err(DeferError::Full);
+        }
+        if let Some(cycle) = self.find_cycle(name, &deps) {
+            return Err(DeferError::Cycle(cycle));
+        }
+        self.deferred.push(Deferred { name: String::from(name), deps, load });
+        Ok(())
+    }
+
+    /// Drop the deferred loads `unwanted` picks, returning how many
+    pub fn cancel(&mut self, mut unwanted: impl FnMut(&Deferred<T>) -> bool) -> usize {
+        let before = self.deferred.len();
+        self.deferred.retain(|deferred| !unwanted(deferred));
+        before - self.deferred.len()
+    }
+
+    /// Remove the deferred loads whose requirements are all available, in
+    /// the order they were deferred
+    pub fn take_ready(&mut self) -> Vec<Deferred<T>> {
+        let mut ready = Vec::new();
+        let mut index = 0;
+        while index < self.deferred.len() {
+            if self.missing(&self.deferred[index].deps).is_empty() {
+                ready.push(self.deferred.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        ready
+    }
+
+    /// A cycle through the new load. Deferred loads already form no cycle
+    /// among themselves, so any the new one closes passes through it
+    fn find_cycle(&self, name: &str, deps: &DriverDeps) -> Option<Cycle> {
+        let mut visited = vec![false; self.deferred.len()];
+        let mut path = Vec::new();
+        let closing = self.reaches(&deps.requires, deps, &mut visited, &mut path)?;
+
+        // path[k] is what the previous driver needs and the deferred load
+        // providing it; the last driver needs `closing` from the new one
+        let mut cycle = Vec::with_capacity(path.len() + 1);
+        let mut driver = String::from(name);
+        for (facility, provider) in path.iter() {
+            cycle.push((driver, facility.clone()));
+            driver = self.deferred[*provider].name.clone();
+        }
+        cycle.push((driver, closing));
+        Some(Cycle(cycle))
+    }
+
+    /// Depth-first search from a driver needing `requires` for one that
+    /// needs something `target` provides; returns that facility
+    fn reaches(
+        &self,
+        requires: &[String],
+        target: &DriverDeps,
+        visited: &mut [bool],
+        path: &mut Vec<(String, usize)>,
+    ) -> Option<String> {
+        for facility in requires.iter().filter(|name| !self.is_available(name)) {
+            if target.provides.contains(facility) {
+                return Some(facility.clone());
+            }
+            for (index, deferred) in self.deferred.iter().enumerate() {
+                if visited[index] || !deferred.deps.provides.contains(facility) {
+                    continue;
+                }
+                visited[index] = true;
+                path.push((facility.clone(), index));
+                if let Some(closing) = self.reaches(&deferred.deps.requires, target, visited, path) {
+                    return Some(closing);
+                }
+                path.pop();
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deps(text: &str) -> DriverDeps {
+        DriverDeps::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parses_declarations() {
+        let gpu = deps("syscalls = process, memory\nrequires = pci, dma , irq, pci\nprovides = gpu # display\n");
+        assert_eq!(gpu.requires, ["pci", "dma", "irq"]);
+        assert_eq!(gpu.provides, ["gpu"]);
+        assert_eq!(deps("memory = 16M"), DriverDeps::default());
+
+        assert_eq!(DriverDeps::parse("ipc = io\nrequires = pci,,dma"), Err(ManifestError::Invalid(2)));
+        assert_eq!(DriverDeps::parse("provides = net stack"), Err(ManifestError::Invalid(1)));
+    }
+
+    #[test]
+    fn test_starts_in_dependency_order() {
+        let mut resolver = Resolver::new();
+        resolver.provide("pci");
+
+        // Loaded before what it needs: the NBD driver waits for the network
+        // stack, which waits for the NIC driver
+        let nbd = deps("requires = net");
+        assert_eq!(resolver.missing(&nbd), ["net"]);
+        resolver.defer("nbd", nbd, 1).unwrap();
+        resolver.defer("netstack", deps("requires = nic\nprovides = net"), 2).unwrap();
+        resolver.defer("e1000", deps("requires = pci, irq\nprovides = nic"), 3).unwrap();
+        assert!(resolver.take_ready().is_empty());
+
+        resolver.provide("irq");
+        let ready = resolver.take_ready();
+        assert_eq!(ready.len(), 1);
+        assert_eq!((ready[0].name.as_str(), ready[0].load), ("e1000", 3));
+
+        // Ready drivers make what they provide available
+        resolver.provide("nic");
+        assert_eq!(resolver.take_ready().iter().map(|load| load.load).collect::<Vec<_>>(), [2]);
+        resolver.provide("net");
+        assert_eq!(resolver.take_ready().iter().map(|load| load.load).collect::<Vec<_>>(), [1]);
+        assert_eq!(resolver.deferred().count(), 0);
+
+        // Unloaded providers take their facilities with them
+        assert!(resolver.withdraw("net") && !resolver.withdraw("net"));
+        resolver.defer("nbd", deps("requires = net"), 4).unwrap();
+        resolver.defer("iscsi", deps("requires = net"), 5).unwrap();
+        assert!(resolver.take_ready().is_empty());
+        assert_eq!(resolver.cancel(|load| load.name == "nbd"), 1);
+        assert_eq!(resolver.deferred().map(|load| load.load).collect::<Vec<_>>(), [5]);
+    }
+
+    #[test]
+    fn test_reports_cycles() {
+        let mut resolver = Resolver::new();
+        resolver.provide("pci");
+        resolver.defer("gpu", deps("requires = pci, dma\nprovides = gpu"), ()).unwrap();
+        resolver.defer("iommu", deps("requires = fence\nprovides = dma"), ()).unwrap();
+
+        let cycle = match resolver.defer("fencer", deps("requires = gpu\nprovides = fence"), ()) {
+            Err(DeferError::Cycle(cycle)) => cycle,
+            _ => panic!("cycle not detected"),
+        };
+        assert_eq!(
+            cycle.describe(),
+            "fencer needs gpu from gpu, gpu needs dma from iommu, iommu needs fence from fencer"
+        );
+        assert_eq!(resolver.deferred().count(), 2);
+
+        // Needing what it provides itself, unless someone else already has
+        let cycle = resolver.defer("loop", deps("requires = x\nprovides = x"), ());
+        assert_eq!(cycle, Err(DeferError::Cycle(Cycle(vec![(String::from("loop"), String::from("x"))]))));
+        resolver.provide("x");
+        assert!(resolver.defer("loop", deps("requires = x, y\nprovides = x"), ()).is_ok());
+    }
+}
@@ -0,0 +1,271 @@
+/*
+ * Orion Operating System - Driver Packages and Device Holds
+ *
+ * Drivers installed at run time. A package is a verified driver image
+ * with its manifest, whose `match` lines name the devices it drives:
+ *
+ *   match = 8086:100e, 8086:10d3   # vendor:device, hexadecimal
+ *   match = 1af4:*                 # any device of a vendor
+ *
+ * The I/O server binds a package to every matching device no driver
+ * claims, when it is installed and when such a device registers later.
+ *
+ * A device in use is held: the fs server holds the device under a mount,
+ * the network server the NIC under open sockets, each hold naming its
+ * holder and what it is for. A driver is not unloaded while a device it
+ * drives is held. Forcing the unload revokes the holds, telling each
+ * holder with a DEVICE_REVOKED event so it fails its own outstanding
+ * operations; the driver is then stopped, and the kernel fails the calls
+ * still queued on its ports.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::sandbox::ManifestError;
+
+/// Most installed packages
+pub const MAX_PACKAGES: usize = 64;
+
+/// Most holds at once, over all devices
+pub const MAX_HOLDS: usize = 1024;
+
+/// Devices a package drives; `device` None matches any of the vendor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceMatch {
+    pub vendor: u16,
+    pub device: Option<u16>,
+}
+
+impl DeviceMatch {
+    fn parse(text: &str) -> Option<Self> {
+        let (vendor, device) = text.split_once(':')?;
+        let hex = |text: &str| if text.len() == 4 { u16::from_str_radix(text, 16).ok() } else { None };
+        let device = match device {
+            "*" => None,
+            device => Some(hex(device)?),
+        };
+        Some(DeviceMatch { vendor: hex(vendor)?, device })
+    }
+
+    pub fn matches(&self, vendor_id: u16, device_id: u16) -> bool {
+        self.vendor == vendor_id && self.device.is_none_or(|device| device == device_id)
+    }
+}
+
+/// The `match` lines of a driver manifest; the other lines are the
+/// sandbox manifest's and the dependency resolver's to check
+pub fn parse_matches(text: &str) -> Result<Vec<DeviceMatch>, ManifestError> {
+    let mut matches = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some(("match", value)) = line.split_once('=').map(|(key, value)| (key.trim(), value)) else {
+            continue;
+        };
+        for entry in value.split(',').map(str::trim) {
+            matches.push(DeviceMatch::parse(entry).ok_or(ManifestError::Invalid(index + 1))?);
+        }
+    }
+    Ok(matches)
+}
+
+pub struct DriverPackage {
+    pub name: String,
+    pub matches: Vec<DeviceMatch>,
+    pub manifest: String,
+    pub image: Vec<u8>,
+}
+
+impl DriverPackage {
+    pub fn drives(&self, vendor_id: u16, device_id: u16) -> bool {
+        self.matches.iter().any(|entry| entry.matches(vendor_id, device_id))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryError {
+    Full,
+    /// A package of that name is installed already
+    Exists,
+}
+
+/// Installed packages, by name
+pub struct DriverRegistry {
+    packages: Vec<Rc<DriverPackage>>,
+}
+
+impl Default for DriverRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DriverRegistry {
+    pub fn new() -> Self {
+        Self { packages: Vec::new() }
+    }
+
+    pub fn install(&mut self, package: DriverPackage) -> Result<Rc<DriverPackage>, RegistryError> {
+        if self.get(&package.name).is_some() {
+            return Err(RegistryError::Exists);
+        }
+        if self.packages.len() >= MAX_PACKAGES {
+            return Err(RegistryError::Full);
+        }
+        let package = Rc::new(package);
+        self.packages.push(package.clone());
+        Ok(package)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Rc<DriverPackage>> {
+        let index = self.packages.iter().position(|package| package.name == name)?;
+        Some(self.packages.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rc<DriverPackage>> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// The first package installed that drives the device
+    pub fn find(&self, vendor_id: u16, device_id: u16) -> Option<Rc<DriverPackage>> {
+        self.packages.iter().find(|package| package.drives(vendor_id, device_id)).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<DriverPackage>> {
+        self.packages.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hold {
+    pub id: u32,
+    pub device: u64,
+    pub holder: u64,
+    /// What the device is held for, "mount /mnt/usb" say
+    pub purpose: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldError {
+    Full,
+    NotFound,
+    /// Released by someone else than its holder
+    NotHolder,
+}
+
+/// References to devices in use
+pub struct HoldTable {
+    holds: Vec<Hold>,
+    next_id: u32,
+}
+
+impl Default for HoldTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HoldTable {
+    pub fn new() -> Self {
+        Self { holds: Vec::new(), next_id: 1 }
+    }
+
+    pub fn hold(&mut self, device: u64, holder: u64, purpose: &str) -> Result<u32, HoldError> {
+        if self.holds.len() >= MAX_HOLDS {
+            return Err(HoldError::Full);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.holds.push(Hold { id, device, holder, purpose: String::from(purpose) });
+        Ok(id)
+    }
+
+    pub fn release(&mut self, id: u32, holder: u64) -> Result<(), HoldError> {
+        let index = self.holds.iter().position(|hold| hold.id == id).ok_or(HoldError::NotFound)?;
+        if self.holds[index].holder != holder {
+            return Err(HoldError::NotHolder);
+        }
+        self.holds.remove(index);
+        Ok(())
+    }
+
+    pub fn of(&self, device: u64) -> impl Iterator<Item = &Hold> {
+        self.holds.iter().filter(move |hold| hold.device == device)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Hold> {
+        self.holds.iter()
+    }
+
+    /// Drop the holds on a device, returning them
+    pub fn revoke(&mut self, device: u64) -> Vec<Hold> {
+        let (revoked, kept) = core::mem::take(&mut self.holds).into_iter().partition(|hold| hold.device == device);
+        self.holds = kept;
+        revoked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, manifest: &str) -> DriverPackage {
+        DriverPackage {
+            name: String::from(name),
+            matches: parse_matches(manifest).unwrap(),
+            manifest: String::from(manifest),
+            image: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_matches_devices() {
+        let e1000 = package("e1000", "syscalls = process\nmatch = 8086:100e, 8086:10D3 # NICs\n");
+        assert!(e1000.drives(0x8086, 0x100e) && e1000.drives(0x8086, 0x10d3));
+        assert!(!e1000.drives(0x8086, 0x1000));
+
+        let virtio = package("virtio", "match = 1af4:*");
+        assert!(virtio.drives(0x1af4, 0x1042) && !virtio.drives(0x8086, 0x1042));
+
+        assert_eq!(parse_matches("memory = 16M\nmatch = 8086:100"), Err(ManifestError::Invalid(2)));
+        assert_eq!(parse_matches("match = 8086"), Err(ManifestError::Invalid(1)));
+
+        let mut registry = DriverRegistry::new();
+        registry.install(e1000).unwrap();
+        registry.install(virtio).unwrap();
+        assert!(matches!(registry.install(package("e1000", "")), Err(RegistryError::Exists)));
+        assert_eq!(registry.find(0x1af4, 0x1001).unwrap().name, "virtio");
+        assert!(registry.find(0x10ec, 0x8139).is_none());
+        assert!(registry.remove("e1000").is_some());
+        assert!(registry.find(0x8086, 0x100e).is_none());
+        assert_eq!(registry.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_counts_holds() {
+        let mut holds = HoldTable::new();
+        let mount = holds.hold(0x10, 4, "mount /mnt/usb").unwrap();
+        let socket = holds.hold(0x20, 7, "socket").unwrap();
+        holds.hold(0x10, 7, "swap").unwrap();
+        assert_eq!(holds.of(0x10).count(), 2);
+
+        assert_eq!(holds.release(mount, 7), Err(HoldError::NotHolder));
+        holds.release(mount, 4).unwrap();
+        assert_eq!(holds.release(mount, 4), Err(HoldError::NotFound));
+        assert_eq!(holds.of(0x10).map(|hold| hold.purpose.as_str()).collect::<Vec<_>>(), ["swap"]);
+
+        let revoked = holds.revoke(0x10);
+        assert_eq!((revoked.len(), revoked[0].holder), (1, 7));
+        assert_eq!(holds.of(0x10).count(), 0);
+        assert_eq!(holds.iter().map(|hold| hold.id).collect::<Vec<_>>(), [socket]);
+    }
+}
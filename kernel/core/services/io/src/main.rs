@@ -1,7 +1,19 @@
 /*
  * Orion Operating System - I/O Server
  *
- * Input/Output management and device control server for Orion OS.
+ * Input/Output management and device control server for Orion OS. The
+ * kernel registers every discovered device together with the capabilities
+ * covering its MMIO windows and DMA; the I/O server starts the matching
+ * driver and hands those capabilities over, but only after the driver image
+ * passed signature verification and was confined by its sandbox profile.
+ * Each load decision is written to the kernel audit log. In permissive mode
+ * failing drivers still start, which is meant for development boards only.
+ * A driver whose manifest requires facilities that are not up yet is
+ * deferred and started once they are, in dependency order (see deps.rs).
+ * Drivers installed at run time are bound to the devices they match, and
+ * are only unloaded while nothing holds those devices unless forced (see
+ * drivers.rs). The requests themselves are served in server.rs; this
+ * file connects it to IPC, the keyring and the kernel.
  *
  * Developed by Jeremy Noverraz (1988-2025)
  * August 2025, Lausanne, Switzerland
@@ -13,13 +25,110 @@
 #![no_std]
 #![no_main]
 
-use orion_ipc::IpcChannel;
+extern crate alloc;
+
+use alloc::vec::Vec;
+
 use orion_cap::Capability;
+use orion_ipc::{lookup, IpcChannel};
+use orion_mac::{CLASS_DRIVER, PERM_LOAD};
+use orion_sys::{audit_emit, kill, mac_check, resume, sandbox_load, spawn_suspended};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod deps;
+mod drivers;
+mod protocol;
+mod sandbox;
+mod server;
+mod signing;
+
+use protocol::{STATUS_EIO, STATUS_EPERM, STATUS_OK};
+use server::{IoHost, IoServer, Outbox};
+
+/// Keyring READ request opcode (see services/keyring/src/protocol.rs)
+const KEYRING_OP_READ: u32 = 3;
+
+/// The kernel and the keyring, as seen by the server
+struct System {
+    keyring: IpcChannel,
+    capabilities: Capability,
+}
+
+impl IoHost for System {
+    fn check_rights(&self, capability: u64, rights: u64, sender: u64) -> bool {
+        self.capabilities.check_rights(capability, rights, sender)
+    }
+
+    fn grant(&mut self, cap: u64, pid: u64, rights: u64) -> bool {
+        self.capabilities.grant(cap, pid, rights)
+    }
+
+    fn spawn_suspended(&mut self, name: &str, image: &[u8]) -> Result<u64, i32> {
+        spawn_suspended(name, image)
+    }
+
+    fn resume(&mut self, pid: u64) -> Result<(), i32> {
+        resume(pid)
+    }
+
+    fn kill(&mut self, pid: u64) {
+        let _ = kill(pid);
+    }
+
+    fn lookup(&self, name: &str) -> Option<u64> {
+        lookup(name)
+    }
+
+    fn sandbox_load(&mut self, pid: u64, profile: &[u8]) -> Result<(), i32> {
+        sandbox_load(pid, profile).map_err(|_| STATUS_EPERM)
+    }
+
+    fn may_load(&self, sender: u64, name: &str) -> Result<(), i32> {
+        mac_check(sender, CLASS_DRIVER, name, PERM_LOAD)
+    }
+
+    fn audit(&mut self, event: u32, record: &str) {
+        let _ = audit_emit(event, record.as_bytes());
+    }
+
+    fn keyring_read(&mut self, handle: u64) -> Result<Vec<u8>, i32> {
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&KEYRING_OP_READ.to_le_bytes());
+        request.extend_from_slice(&handle.to_le_bytes());
+
+        let response = self.keyring.call(&request).map_err(|_| STATUS_EIO)?;
+        if response.len() < 4 {
+            return Err(STATUS_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+}
 
 fn main() {
-    let _channel = IpcChannel::new();
-    let _capability = Capability::new();
-    // TODO: Implement I/O management server
+    let system = System { keyring: IpcChannel::connect("keyring"), capabilities: Capability::new() };
+    let mut server = IoServer::new(system);
+    let ipc_channel = IpcChannel::new();
+    let mut outbox = Outbox::new();
+
+    loop {
+        match ipc_channel.receive() {
+            Some(message) => {
+                server.handle(message.sender, message.capability, &message.data, &mut outbox);
+                for (recipient, message) in outbox.drain(..) {
+                    ipc_channel.send(recipient, &message);
+                }
+            }
+            None => ipc_channel.wait(),
+        }
+    }
 }
 
 #[panic_handler]
@@ -0,0 +1,214 @@
+/*
+ * Orion Operating System - I/O Server Protocol
+ *
+ * Wire format of the requests understood by the I/O server. All fields are
+ * little-endian; every message starts with a 32-bit opcode and every reply
+ * starts with a 32-bit signed status (0 or a negative errno).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_REGISTER_DEVICE: u32 = 1;
+pub const OP_LOAD_DRIVER: u32 = 2;
+pub const OP_ENROLL_KEY: u32 = 3;
+pub const OP_LOCK_KEYS: u32 = 4;
+pub const OP_SET_POLICY: u32 = 5;
+pub const OP_STATUS: u32 = 6;
+pub const OP_APPLY_SANDBOX: u32 = 7;
+pub const OP_LIST_DEVICES: u32 = 8;
+pub const OP_TRUST_ANCHORS: u32 = 9;
+pub const OP_PROVIDE: u32 = 10;
+pub const OP_DRIVER_READY: u32 = 11;
+pub const OP_LIST_DEFERRED: u32 = 12;
+pub const OP_INSTALL_DRIVER: u32 = 13;
+pub const OP_UNLOAD_DRIVER: u32 = 14;
+pub const OP_DEVICE_HOLD: u32 = 15;
+pub const OP_DEVICE_RELEASE: u32 = 16;
+pub const OP_LIST_HOLDS: u32 = 17;
+
+// Events sent to holders, unasked: opcode, device handle, hold id
+pub const EVENT_DEVICE_REVOKED: u32 = 0x100;
+
+// UNLOAD_DRIVER flags
+pub const UNLOAD_FORCE: u32 = 1 << 0;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EEXIST: i32 = -17;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+pub const STATUS_ELOOP: i32 = -40;
+pub const STATUS_EKEYREJECTED: i32 = -129;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IoRequest {
+    RegisterDevice { device: u64, vendor_id: u16, device_id: u16, mmio: DeviceWindow, dma: DeviceWindow },
+    LoadDriver { device: u64, name: String, manifest: String, image: Vec<u8> },
+    EnrollKey { keyring_handle: u64 },
+    LockKeys,
+    SetPolicy { policy: u32 },
+    Status,
+    ApplySandbox { pid: u64, manifest: String },
+    ListDevices,
+    /// Locked driver signing keys, for drivers checking firmware images
+    TrustAnchors,
+    /// A facility drivers may require is up (see deps.rs)
+    Provide { name: String },
+    /// Sent by a driver once what its manifest provides is usable
+    DriverReady,
+    ListDeferred,
+    /// Keep a driver image and bind it to the unclaimed devices it matches
+    InstallDriver { name: String, manifest: String, image: Vec<u8> },
+    UnloadDriver { name: String, flags: u32 },
+    DeviceHold { device: u64, purpose: String },
+    DeviceRelease { hold: u32 },
+    ListHolds,
+}
+
+/// Capability covering a device address window, and the window itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceWindow {
+    pub cap: u64,
+    pub base: u64,
+    pub size: u64,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+fn read_window(data: &[u8], offset: usize) -> Option<DeviceWindow> {
+    Some(DeviceWindow {
+        cap: read_u64(data, offset)?,
+        base: read_u64(data, offset + 8)?,
+        size: read_u64(data, offset + 16)?,
+    })
+}
+
+/// Decode a `len, utf-8 bytes` field, returning it with the offset past it
+fn read_string(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some((String::from(core::str::from_utf8(bytes).ok()?), offset + 4 + len))
+}
+
+impl IoRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_REGISTER_DEVICE => Some(IoRequest::RegisterDevice {
+                device: read_u64(data, 4)?,
+                vendor_id: read_u16(data, 12)?,
+                device_id: read_u16(data, 14)?,
+                mmio: read_window(data, 16)?,
+                dma: read_window(data, 40)?,
+            }),
+            OP_LOAD_DRIVER => {
+                // device, name, sandbox manifest (may be empty), image
+                let device = read_u64(data, 4)?;
+                let (name, offset) = read_string(data, 12)?;
+                let (manifest, offset) = read_string(data, offset)?;
+                let image = data.get(offset..)?.to_vec();
+                Some(IoRequest::LoadDriver { device, name, manifest, image })
+            }
+            OP_ENROLL_KEY => Some(IoRequest::EnrollKey { keyring_handle: read_u64(data, 4)? }),
+            OP_LOCK_KEYS => Some(IoRequest::LockKeys),
+            OP_SET_POLICY => Some(IoRequest::SetPolicy { policy: read_u32(data, 4)? }),
+            OP_STATUS => Some(IoRequest::Status),
+            OP_APPLY_SANDBOX => Some(IoRequest::ApplySandbox {
+                pid: read_u64(data, 4)?,
+                manifest: read_string(data, 12)?.0,
+            }),
+            OP_LIST_DEVICES => Some(IoRequest::ListDevices),
+            OP_TRUST_ANCHORS => Some(IoRequest::TrustAnchors),
+            OP_PROVIDE => Some(IoRequest::Provide { name: read_string(data, 4)?.0 }),
+            OP_DRIVER_READY => Some(IoRequest::DriverReady),
+            OP_LIST_DEFERRED => Some(IoRequest::ListDeferred),
+            OP_INSTALL_DRIVER => {
+                // name, manifest, image
+                let (name, offset) = read_string(data, 4)?;
+                let (manifest, offset) = read_string(data, offset)?;
+                let image = data.get(offset..)?.to_vec();
+                Some(IoRequest::InstallDriver { name, manifest, image })
+            }
+            OP_UNLOAD_DRIVER => {
+                let (name, offset) = read_string(data, 4)?;
+                Some(IoRequest::UnloadDriver { name, flags: read_u32(data, offset)? })
+            }
+            OP_DEVICE_HOLD => Some(IoRequest::DeviceHold { device: read_u64(data, 4)?, purpose: read_string(data, 12)?.0 }),
+            OP_DEVICE_RELEASE => Some(IoRequest::DeviceRelease { hold: read_u32(data, 4)? }),
+            OP_LIST_HOLDS => Some(IoRequest::ListHolds),
+            _ => None,
+        }
+    }
+}
+
+/// LIST_DEVICES record: handle, vendor and device ids, driver pid (0 when
+/// unbound) and driver name as a `len, utf-8 bytes` field
+pub fn encode_device(handle: u64, vendor_id: u16, device_id: u16, driver_pid: u64, driver: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&handle.to_le_bytes());
+    out.extend_from_slice(&vendor_id.to_le_bytes());
+    out.extend_from_slice(&device_id.to_le_bytes());
+    out.extend_from_slice(&driver_pid.to_le_bytes());
+    out.extend_from_slice(&(driver.len() as u32).to_le_bytes());
+    out.extend_from_slice(driver.as_bytes());
+}
+
+/// LIST_DEFERRED record: device handle, driver name, then the count of
+/// requirements still missing and each as a `len, utf-8 bytes` field
+pub fn encode_deferred(handle: u64, driver: &str, missing: &[&str], out: &mut Vec<u8>) {
+    out.extend_from_slice(&handle.to_le_bytes());
+    out.extend_from_slice(&(driver.len() as u32).to_le_bytes());
+    out.extend_from_slice(driver.as_bytes());
+    out.extend_from_slice(&(missing.len() as u32).to_le_bytes());
+    for facility in missing {
+        out.extend_from_slice(&(facility.len() as u32).to_le_bytes());
+        out.extend_from_slice(facility.as_bytes());
+    }
+}
+
+/// LIST_HOLDS record, also the payload of an UNLOAD_DRIVER refused with
+/// EBUSY: hold id, device handle, holder and purpose as a `len, utf-8
+/// bytes` field
+pub fn encode_hold(id: u32, device: u64, holder: u64, purpose: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&id.to_le_bytes());
+    out.extend_from_slice(&device.to_le_bytes());
+    out.extend_from_slice(&holder.to_le_bytes());
+    out.extend_from_slice(&(purpose.len() as u32).to_le_bytes());
+    out.extend_from_slice(purpose.as_bytes());
+}
+
+/// DEVICE_REVOKED event for the holder of `hold`
+pub fn revoked_event(device: u64, hold: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16);
+    out.extend_from_slice(&EVENT_DEVICE_REVOKED.to_le_bytes());
+    out.extend_from_slice(&device.to_le_bytes());
+    out.extend_from_slice(&hold.to_le_bytes());
+    out
+}
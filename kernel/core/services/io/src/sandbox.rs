@@ -0,0 +1,310 @@
+/*
+ * Orion Operating System - Sandbox Manifests
+ *
+ * Declarative sandbox manifests for drivers and servers. A manifest is a
+ * short `key = value` text shipped next to the binary:
+ *
+ *   # Intel e1000 NIC
+ *   syscalls = process, memory, ipc, time
+ *   ipc = net, entropy
+ *   memory = 16M
+ *   bus = device
+ *   on_violation = terminate
+ *
+ * `ipc = any` lifts the IPC restriction for servers that answer arbitrary
+ * clients. `bus` accepts `device` (the windows of the device being bound)
+ * or an explicit `base:size` window and may be repeated. `requires` and
+ * `provides` lines declare driver dependencies and are left to deps.rs,
+ * `match` lines to drivers.rs. The manifest is
+ * turned into the kernel sandbox_profile_t layout (see
+ * capabilities/sandbox.h) and loaded with SYS_SANDBOX_LOAD before the
+ * process first runs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_sys::syscalls::*;
+
+// Limits and layout of sandbox_profile_t
+pub const SANDBOX_MAX_PEERS: usize = 16;
+pub const SANDBOX_MAX_BUS_RANGES: usize = 8;
+pub const SANDBOX_SYSCALL_WORDS: usize = 8;
+pub const SANDBOX_FLAG_TERMINATE: u32 = 1 << 0;
+pub const SANDBOX_FLAG_ANY_PEER: u32 = 1 << 1;
+pub const SANDBOX_PROFILE_SIZE: usize =
+    8 + SANDBOX_SYSCALL_WORDS * 8 + SANDBOX_MAX_PEERS * 8 + SANDBOX_MAX_BUS_RANGES * 16 + 16;
+
+/// Profile applied to drivers that ship without a manifest
+pub const DEFAULT_DRIVER_MANIFEST: &str = "\
+syscalls = process, memory, ipc, time, io, mac
+ipc = io
+memory = 32M
+bus = device
+on_violation = audit
+";
+
+/// Syscalls granted by each manifest group
+const SYSCALL_GROUPS: &[(&str, &[u32])] = &[
+    ("process", &[SYS_EXIT, SYS_YIELD, SYS_GETPID, SYS_GETTID, SYS_THREAD_CREATE, SYS_WAIT, SYS_SIGNAL]),
+    ("memory", &[SYS_VM_MAP, SYS_VM_UNMAP, SYS_VM_PROTECT, SYS_SHM_CREATE, SYS_SHM_ATTACH, SYS_SHM_DETACH, SYS_MADVISE]),
+    (
+        "ipc",
+        &[
+            SYS_PORT_CREATE,
+            SYS_PORT_SEND,
+            SYS_PORT_RECV,
+            SYS_PORT_SHARE,
+            SYS_MSG_FORWARD,
+            SYS_PORT_CALL,
+            SYS_PORT_REPLY_RECV,
+        ],
+    ),
+    ("time", &[SYS_CLOCK_GET, SYS_TIMER_CREATE, SYS_TIMER_START, SYS_TIMER_STOP, SYS_NANOSLEEP]),
+    ("clock", &[SYS_CLOCK_ADJUST]),
+    ("cpufreq", &[SYS_CPUFREQ]),
+    ("realtime", &[SYS_SCHED_ATTR]),
+    ("hypervisor", &[SYS_HV_CTL]),
+    ("io", &[SYS_IO_SUBMIT, SYS_IO_POLL, SYS_IO_CANCEL]),
+    ("objects", &[SYS_OBJ_INFO, SYS_OBJ_DUP, SYS_OBJ_CLOSE]),
+    ("random", &[SYS_RANDOM]),
+    ("audit", &[SYS_AUDIT_EMIT]),
+    ("mac", &[SYS_MAC_CTL]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestError {
+    /// Syntax error or unknown key/value on the given 1-based line
+    Invalid(usize),
+    /// More peers or bus windows than a profile can hold
+    TooMany,
+    /// An `ipc` entry names an endpoint that does not exist
+    UnknownEndpoint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusRange {
+    pub base: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SandboxManifest {
+    pub syscalls: [u64; SANDBOX_SYSCALL_WORDS],
+    pub peers: Vec<String>,
+    pub any_peer: bool,
+    pub max_memory: u64,
+    pub bus_ranges: Vec<BusRange>,
+    pub bind_device: bool,
+    pub terminate: bool,
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Byte size with an optional K/M/G suffix
+fn parse_size(text: &str) -> Option<u64> {
+    let (digits, shift) = match text.as_bytes().last()? {
+        b'K' => (&text[..text.len() - 1], 10),
+        b'M' => (&text[..text.len() - 1], 20),
+        b'G' => (&text[..text.len() - 1], 30),
+        _ => (text, 0),
+    };
+    parse_number(digits)?.checked_mul(1 << shift)
+}
+
+fn allow_syscall(bitmap: &mut [u64; SANDBOX_SYSCALL_WORDS], number: usize) {
+    if number < SANDBOX_SYSCALL_WORDS * 64 {
+        bitmap[number / 64] |= 1 << (number % 64);
+    }
+}
+
+impl SandboxManifest {
+    pub fn parse(text: &str) -> Result<Self, ManifestError> {
+        let mut manifest = SandboxManifest {
+            syscalls: [0; SANDBOX_SYSCALL_WORDS],
+            peers: Vec::new(),
+            any_peer: false,
+            max_memory: 0,
+            bus_ranges: Vec::new(),
+            bind_device: false,
+            terminate: false,
+        };
+
+        // A process must always be able to exit
+        allow_syscall(&mut manifest.syscalls, SYS_EXIT as usize);
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = ManifestError::Invalid(index + 1);
+            let (key, value) = line.split_once('=').ok_or(invalid)?;
+            let value = value.trim();
+
+            match key.trim() {
+                "syscalls" => {
+                    for group in value.split(',').map(str::trim) {
+                        let (_, numbers) = SYSCALL_GROUPS
+                            .iter()
+                            .find(|(name, _)| *name == group)
+                            .ok_or(invalid)?;
+                        for number in numbers.iter() {
+                            allow_syscall(&mut manifest.syscalls, *number as usize);
+                        }
+                    }
+                }
+                "ipc" if value == "any" => manifest.any_peer = true,
+                "ipc" => {
+                    for peer in value.split(',').map(str::trim).filter(|peer| !peer.is_empty()) {
+                        manifest.peers.push(String::from(peer));
+                    }
+                }
+                "memory" => manifest.max_memory = parse_size(value).ok_or(invalid)?,
+                "bus" if value == "device" => manifest.bind_device = true,
+                "bus" => {
+                    let (base, size) = value.split_once(':').ok_or(invalid)?;
+                    let base = parse_number(base.trim()).ok_or(invalid)?;
+                    let size = parse_size(size.trim()).ok_or(invalid)?;
+                    manifest.bus_ranges.push(BusRange { base, size });
+                }
+                "on_violation" => {
+                    manifest.terminate = match value {
+                        "terminate" => true,
+                        "audit" => false,
+                        _ => return Err(invalid),
+                    }
+                }
+                // Load ordering and the devices driven, read by deps.rs
+                // and drivers.rs
+                "requires" | "provides" | "match" => {}
+                _ => return Err(invalid),
+            }
+        }
+
+        if manifest.peers.len() > SANDBOX_MAX_PEERS {
+            return Err(ManifestError::TooMany);
+        }
+        Ok(manifest)
+    }
+
+    /// Encode as a sandbox_profile_t, resolving endpoint names with `lookup`
+    /// and adding `device_ranges` when the manifest binds the device
+    pub fn to_profile(
+        &self,
+        lookup: impl Fn(&str) -> Option<u64>,
+        device_ranges: &[BusRange],
+    ) -> Result<Vec<u8>, ManifestError> {
+        let mut ranges: Vec<BusRange> = self.bus_ranges.clone();
+        if self.bind_device {
+            ranges.extend(device_ranges.iter().filter(|range| range.size != 0));
+        }
+        if ranges.len() > SANDBOX_MAX_BUS_RANGES {
+            return Err(ManifestError::TooMany);
+        }
+
+        let mut peers = [0u64; SANDBOX_MAX_PEERS];
+        for (slot, name) in peers.iter_mut().zip(self.peers.iter()) {
+            *slot = lookup(name).ok_or(ManifestError::UnknownEndpoint)?;
+        }
+
+        let mut out = Vec::with_capacity(SANDBOX_PROFILE_SIZE);
+        out.extend_from_slice(&self.max_memory.to_le_bytes());
+        for word in self.syscalls.iter() {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        for peer in peers.iter() {
+            out.extend_from_slice(&peer.to_le_bytes());
+        }
+        for i in 0..SANDBOX_MAX_BUS_RANGES {
+            let range = ranges.get(i).copied().unwrap_or(BusRange { base: 0, size: 0 });
+            out.extend_from_slice(&range.base.to_le_bytes());
+            out.extend_from_slice(&range.size.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.peers.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(ranges.len() as u32).to_le_bytes());
+        let mut flags = 0;
+        if self.terminate {
+            flags |= SANDBOX_FLAG_TERMINATE;
+        }
+        if self.any_peer {
+            flags |= SANDBOX_FLAG_ANY_PEER;
+        }
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NIC_MANIFEST: &str = "\
+# Intel e1000 NIC
+syscalls = process, memory, ipc, time
+ipc = net, entropy
+memory = 16M
+bus = device
+bus = 0xfebc0000:128K
+on_violation = terminate
+";
+
+    #[test]
+    fn test_parses_manifest() {
+        let manifest = SandboxManifest::parse(NIC_MANIFEST).unwrap();
+        assert_eq!(manifest.peers, ["net", "entropy"]);
+        assert_eq!(manifest.max_memory, 16 << 20);
+        assert_eq!(manifest.bus_ranges, [BusRange { base: 0xfebc0000, size: 128 << 10 }]);
+        assert!(manifest.bind_device && manifest.terminate);
+
+        let word = manifest.syscalls[SYS_PORT_SEND as usize / 64];
+        assert_ne!(word & (1 << (SYS_PORT_SEND as usize % 64)), 0);
+        let word = manifest.syscalls[SYS_RANDOM as usize / 64];
+        assert_eq!(word & (1 << (SYS_RANDOM as usize % 64)), 0);
+
+        assert_eq!(SandboxManifest::parse("ipc = net\nsyscalls = everything\n"), Err(ManifestError::Invalid(2)));
+        assert_eq!(SandboxManifest::parse("memory 16M"), Err(ManifestError::Invalid(1)));
+        assert!(SandboxManifest::parse(DEFAULT_DRIVER_MANIFEST).is_ok());
+    }
+
+    #[test]
+    fn test_encodes_kernel_profile() {
+        let manifest = SandboxManifest::parse(NIC_MANIFEST).unwrap();
+        let lookup = |name: &str| match name {
+            "net" => Some(3),
+            "entropy" => Some(7),
+            _ => None,
+        };
+        let device = [BusRange { base: 0xfeb00000, size: 0x1000 }, BusRange { base: 0, size: 0 }];
+
+        let profile = manifest.to_profile(lookup, &device).unwrap();
+        assert_eq!(profile.len(), SANDBOX_PROFILE_SIZE);
+        assert_eq!(profile[72..80], 3u64.to_le_bytes());
+        assert_eq!(profile[80..88], 7u64.to_le_bytes());
+
+        let counts = SANDBOX_PROFILE_SIZE - 16;
+        assert_eq!(profile[counts..counts + 4], 2u32.to_le_bytes());
+        assert_eq!(profile[counts + 4..counts + 8], 2u32.to_le_bytes());
+        assert_eq!(profile[counts + 8..counts + 12], SANDBOX_FLAG_TERMINATE.to_le_bytes());
+
+        let server = SandboxManifest::parse("ipc = any").unwrap().to_profile(lookup, &[]).unwrap();
+        assert_eq!(server[counts + 8..counts + 12], SANDBOX_FLAG_ANY_PEER.to_le_bytes());
+
+        let unknown = SandboxManifest::parse("ipc = nowhere").unwrap();
+        assert_eq!(unknown.to_profile(lookup, &[]), Err(ManifestError::UnknownEndpoint));
+    }
+}
@@ -0,0 +1,820 @@
+/*
+ * Orion Operating System - I/O Server Dispatch
+ *
+ * The I/O server's state and request handling, kept apart from IPC and
+ * system calls so that it runs against a fake system in the tests. What
+ * the server asks of the kernel (starting, confining and stopping driver
+ * processes, granting device capabilities, checking rights, the MAC
+ * policy and the audit log) and of the keyring goes through IoHost.
+ * Replies, and the DEVICE_REVOKED events an unload sends to holders, are
+ * returned as (recipient, message) pairs rather than sent.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+
+use crate::deps::{self, DeferError, DriverDeps, Resolver};
+use crate::drivers::{self, DriverPackage, DriverRegistry, HoldError, HoldTable, RegistryError};
+use crate::protocol::*;
+use crate::sandbox::{BusRange, SandboxManifest, DEFAULT_DRIVER_MANIFEST};
+use crate::signing::{Policy, TrustError, TrustStore, Verdict};
+
+/// Only the kernel may register devices
+pub const KERNEL_ENDPOINT: u64 = 0;
+
+/// Audit event types emitted by the I/O server (user range, see capabilities.c)
+pub const AUDIT_DRIVER_LOAD: u32 = 0x1001;
+pub const AUDIT_DRIVER_POLICY: u32 = 0x1002;
+pub const AUDIT_DRIVER_DEPS: u32 = 0x1003;
+pub const AUDIT_DRIVER_UNLOAD: u32 = 0x1004;
+
+const MAX_DEVICES: usize = 256;
+
+/// Messages to send, with their recipient
+pub type Outbox = Vec<(u64, Vec<u8>)>;
+
+/// What the server needs from the system
+pub trait IoHost {
+    /// Whether `capability`, presented by `sender`, carries `rights`
+    fn check_rights(&self, capability: u64, rights: u64, sender: u64) -> bool;
+    /// Give `pid` `rights` on the capability `cap`
+    fn grant(&mut self, cap: u64, pid: u64, rights: u64) -> bool;
+    /// Create the process of a driver image without running it
+    fn spawn_suspended(&mut self, name: &str, image: &[u8]) -> Result<u64, i32>;
+    fn resume(&mut self, pid: u64) -> Result<(), i32>;
+    fn kill(&mut self, pid: u64);
+    /// Endpoint registered under `name`, for the peers of a sandbox
+    fn lookup(&self, name: &str) -> Option<u64>;
+    fn sandbox_load(&mut self, pid: u64, profile: &[u8]) -> Result<(), i32>;
+    /// MAC decision on `sender` loading or unloading driver `name`
+    fn may_load(&self, sender: u64, name: &str) -> Result<(), i32>;
+    fn audit(&mut self, event: u32, record: &str);
+    /// Key stored in the keyring under `handle`
+    fn keyring_read(&mut self, handle: u64) -> Result<Vec<u8>, i32>;
+}
+
+struct Device {
+    handle: u64,
+    vendor_id: u16,
+    device_id: u16,
+    mmio: DeviceWindow,
+    dma: DeviceWindow,
+    driver_pid: Option<u64>,
+    driver: String,
+    /// Facilities the driver makes available once it reports ready
+    provides: Vec<String>,
+    ready: bool,
+}
+
+/// A verified driver image waiting for its requirements
+struct PendingLoad {
+    device: u64,
+    manifest: String,
+    image: Vec<u8>,
+}
+
+pub struct IoServer<H: IoHost> {
+    host: H,
+    devices: Vec<Device>,
+    trust: TrustStore,
+    policy: Policy,
+    loads_allowed: u64,
+    loads_denied: u64,
+    resolver: Resolver<PendingLoad>,
+    packages: DriverRegistry,
+    holds: HoldTable,
+}
+
+impl<H: IoHost> IoServer<H> {
+    pub fn new(host: H) -> Self {
+        Self {
+            host,
+            devices: Vec::new(),
+            trust: TrustStore::new(),
+            policy: Policy::Enforcing,
+            loads_allowed: 0,
+            loads_denied: 0,
+            resolver: Resolver::new(),
+            packages: DriverRegistry::new(),
+            holds: HoldTable::new(),
+        }
+    }
+
+    /// Run one request from `sender`, presented with `capability`, and
+    /// queue its reply
+    pub fn handle(&mut self, sender: u64, capability: u64, data: &[u8], outbox: &mut Outbox) {
+        let request = match IoRequest::decode(data) {
+            Some(request) => request,
+            None => {
+                outbox.push((sender, reply(STATUS_EINVAL, &[])));
+                return;
+            }
+        };
+
+        let authorized = match request {
+            IoRequest::RegisterDevice { .. } => sender == KERNEL_ENDPOINT,
+            IoRequest::Provide { .. } => {
+                sender == KERNEL_ENDPOINT || self.host.check_rights(capability, CAP_ADMIN, sender)
+            }
+            IoRequest::DriverReady => self.devices.iter().any(|device| device.driver_pid == Some(sender)),
+            IoRequest::Status | IoRequest::ListDevices | IoRequest::ListDeferred | IoRequest::ListHolds => {
+                self.host.check_rights(capability, CAP_READ, sender)
+            }
+            // Servers using a device: the fs server for mounts, the network
+            // server for sockets
+            IoRequest::DeviceHold { .. } | IoRequest::DeviceRelease { .. } => {
+                self.host.check_rights(capability, CAP_WRITE, sender)
+            }
+            // Drivers we started fetch the keys to check firmware images with
+            IoRequest::TrustAnchors => {
+                self.devices.iter().any(|device| device.driver_pid == Some(sender))
+                    || self.host.check_rights(capability, CAP_READ, sender)
+            }
+            _ => self.host.check_rights(capability, CAP_ADMIN, sender),
+        };
+        if !authorized {
+            outbox.push((sender, reply(STATUS_EPERM, &[])));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let status = match request {
+            IoRequest::RegisterDevice { device, vendor_id, device_id, mmio, dma } => {
+                self.register_device(device, vendor_id, device_id, mmio, dma)
+            }
+            IoRequest::LoadDriver { device, name, manifest, image } => {
+                self.load_driver(sender, device, &name, &manifest, &image, &mut payload)
+            }
+            IoRequest::EnrollKey { keyring_handle } => self.enroll_key(keyring_handle, &mut payload),
+            IoRequest::LockKeys => {
+                self.trust.lock();
+                STATUS_OK
+            }
+            IoRequest::SetPolicy { policy } => self.set_policy(sender, policy),
+            IoRequest::Status => {
+                payload.extend_from_slice(&self.policy.as_u32().to_le_bytes());
+                payload.extend_from_slice(&(self.trust.is_locked() as u32).to_le_bytes());
+                payload.extend_from_slice(&(self.trust.len() as u32).to_le_bytes());
+                payload.extend_from_slice(&(self.devices.len() as u32).to_le_bytes());
+                payload.extend_from_slice(&self.loads_allowed.to_le_bytes());
+                payload.extend_from_slice(&self.loads_denied.to_le_bytes());
+                payload.extend_from_slice(&(self.resolver.deferred().count() as u32).to_le_bytes());
+                STATUS_OK
+            }
+            IoRequest::ApplySandbox { pid, manifest } => match self.confine(pid, &manifest, &[]) {
+                Ok(()) => STATUS_OK,
+                Err(status) => status,
+            },
+            IoRequest::ListDevices => {
+                for device in self.devices.iter() {
+                    let pid = device.driver_pid.unwrap_or(0);
+                    encode_device(device.handle, device.vendor_id, device.device_id, pid, &device.driver, &mut payload);
+                }
+                STATUS_OK
+            }
+            IoRequest::TrustAnchors => self.trust_anchors(&mut payload),
+            IoRequest::Provide { name } => self.provide(&name),
+            IoRequest::DriverReady => self.driver_ready(sender),
+            IoRequest::ListDeferred => {
+                payload.extend_from_slice(&(self.resolver.deferred().count() as u32).to_le_bytes());
+                for deferred in self.resolver.deferred() {
+                    let missing = self.resolver.missing(&deferred.deps);
+                    encode_deferred(deferred.load.device, &deferred.name, &missing, &mut payload);
+                }
+                STATUS_OK
+            }
+            IoRequest::InstallDriver { name, manifest, image } => {
+                self.install_driver(sender, name, manifest, image, &mut payload)
+            }
+            IoRequest::UnloadDriver { name, flags } => {
+                self.unload_driver(sender, &name, flags & UNLOAD_FORCE != 0, &mut payload, outbox)
+            }
+            IoRequest::DeviceHold { device, purpose } => self.hold_device(sender, device, &purpose, &mut payload),
+            IoRequest::DeviceRelease { hold } => match self.holds.release(hold, sender) {
+                Ok(()) => STATUS_OK,
+                Err(HoldError::NotHolder) => STATUS_EPERM,
+                Err(_) => STATUS_ENOENT,
+            },
+            IoRequest::ListHolds => {
+                for hold in self.holds.iter() {
+                    encode_hold(hold.id, hold.device, hold.holder, &hold.purpose, &mut payload);
+                }
+                STATUS_OK
+            }
+        };
+
+        outbox.push((sender, reply(status, &payload)));
+    }
+
+    fn register_device(
+        &mut self,
+        handle: u64,
+        vendor_id: u16,
+        device_id: u16,
+        mmio: DeviceWindow,
+        dma: DeviceWindow,
+    ) -> i32 {
+        if self.devices.iter().any(|device| device.handle == handle) {
+            return STATUS_EEXIST;
+        }
+        if self.devices.len() >= MAX_DEVICES {
+            return STATUS_ENOSPC;
+        }
+
+        self.devices.push(Device {
+            handle,
+            vendor_id,
+            device_id,
+            mmio,
+            dma,
+            driver_pid: None,
+            driver: String::new(),
+            provides: Vec::new(),
+            ready: false,
+        });
+
+        // A driver installed earlier may drive it
+        if let Some(package) = self.packages.find(vendor_id, device_id) {
+            let (name, manifest, image) = (&package.name, &package.manifest, &package.image);
+            let _ = self.load_driver(KERNEL_ENDPOINT, handle, name, manifest, image, &mut Vec::new());
+        }
+        STATUS_OK
+    }
+
+    /// Verify a driver image and, if policy allows it, start it on `handle`
+    /// confined by `manifest` (or the default driver profile when empty).
+    /// A driver whose requirements are not all available yet is deferred
+    /// and the reply carries pid 0
+    fn load_driver(
+        &mut self,
+        sender: u64,
+        handle: u64,
+        name: &str,
+        manifest: &str,
+        image: &[u8],
+        out: &mut Vec<u8>,
+    ) -> i32 {
+        let index = match self.devices.iter().position(|device| device.handle == handle) {
+            Some(index) => index,
+            None => return STATUS_ENOENT,
+        };
+        if self.devices[index].driver_pid.is_some() || self.resolver.deferred().any(|load| load.load.device == handle) {
+            return STATUS_EBUSY;
+        }
+
+        // A broken manifest is reported now rather than when a deferred
+        // driver finally starts
+        let manifest = if manifest.is_empty() { DEFAULT_DRIVER_MANIFEST } else { manifest };
+        let deps = match (SandboxManifest::parse(manifest), DriverDeps::parse(manifest)) {
+            (Ok(_), Ok(deps)) => deps,
+            _ => return STATUS_EINVAL,
+        };
+
+        let verdict = self.trust.verify(image);
+        let allowed = self.policy.allows(&verdict);
+        self.audit_load(sender, index, name, &verdict, allowed);
+
+        if !allowed {
+            self.loads_denied += 1;
+            return STATUS_EKEYREJECTED;
+        }
+
+        if !self.resolver.missing(&deps).is_empty() {
+            let load = PendingLoad { device: handle, manifest: String::from(manifest), image: image.to_vec() };
+            return match self.resolver.defer(name, deps, load) {
+                Ok(()) => {
+                    out.extend_from_slice(&0u64.to_le_bytes());
+                    STATUS_OK
+                }
+                Err(DeferError::Full) => STATUS_ENOSPC,
+                Err(DeferError::Cycle(cycle)) => {
+                    let text = cycle.describe();
+                    let record = format!("driver-deps cycle name={} device={:#x}: {}", name, handle, text);
+                    self.host.audit(AUDIT_DRIVER_DEPS, &record);
+                    out.extend_from_slice(text.as_bytes());
+                    STATUS_ELOOP
+                }
+            };
+        }
+
+        match self.start_driver(index, name, manifest, image, deps.provides) {
+            Ok(pid) => {
+                out.extend_from_slice(&pid.to_le_bytes());
+                STATUS_OK
+            }
+            Err(status) => status,
+        }
+    }
+
+    /// Start a verified driver image on the device at `index`
+    fn start_driver(
+        &mut self,
+        index: usize,
+        name: &str,
+        manifest: &str,
+        image: &[u8],
+        provides: Vec<String>,
+    ) -> Result<u64, i32> {
+        let pid = self.host.spawn_suspended(name, image).map_err(|_| STATUS_EIO)?;
+
+        // The sandbox must be in place before the driver runs or holds any device
+        let windows = [self.devices[index].mmio, self.devices[index].dma]
+            .map(|window| BusRange { base: window.base, size: window.size });
+        if let Err(status) = self.confine(pid, manifest, &windows) {
+            self.host.kill(pid);
+            return Err(status);
+        }
+
+        // Device access is only handed out once the image is accepted
+        let device = &mut self.devices[index];
+        let rights = CAP_READ | CAP_WRITE;
+        if !self.host.grant(device.mmio.cap, pid, rights)
+            || !self.host.grant(device.dma.cap, pid, rights)
+            || self.host.resume(pid).is_err()
+        {
+            self.host.kill(pid);
+            return Err(STATUS_EIO);
+        }
+
+        device.driver_pid = Some(pid);
+        device.driver = String::from(name);
+        device.provides = provides;
+        device.ready = false;
+        self.loads_allowed += 1;
+        Ok(pid)
+    }
+
+    fn provide(&mut self, name: &str) -> i32 {
+        if !deps::valid_facility(name) {
+            return STATUS_EINVAL;
+        }
+        if self.resolver.provide(name) {
+            self.start_deferred();
+        }
+        STATUS_OK
+    }
+
+    /// A driver we started is up: what it provides becomes available
+    fn driver_ready(&mut self, pid: u64) -> i32 {
+        let device = match self.devices.iter_mut().find(|device| device.driver_pid == Some(pid)) {
+            Some(device) => device,
+            None => return STATUS_ENOENT,
+        };
+        if device.ready {
+            return STATUS_OK;
+        }
+        device.ready = true;
+        let mut added = false;
+        for facility in device.provides.iter() {
+            added |= self.resolver.provide(facility);
+        }
+        if added {
+            self.start_deferred();
+        }
+        STATUS_OK
+    }
+
+    /// Start the deferred drivers whose requirements are now all available.
+    /// What they provide only counts once they report ready, so a driver
+    /// started here never satisfies another one in the same pass
+    fn start_deferred(&mut self) {
+        for deferred in self.resolver.take_ready() {
+            let load = deferred.load;
+            let provides = deferred.deps.provides;
+            let started = match self.devices.iter().position(|device| device.handle == load.device) {
+                Some(index) => self.start_driver(index, &deferred.name, &load.manifest, &load.image, provides),
+                None => Err(STATUS_ENOENT),
+            };
+            if let Err(status) = started {
+                let record = format!(
+                    "driver-deps start-failed name={} device={:#x} status={}",
+                    deferred.name, load.device, status
+                );
+                self.host.audit(AUDIT_DRIVER_DEPS, &record);
+            }
+        }
+    }
+
+    /// Keep a driver image for the devices its manifest matches and bind it
+    /// to those no driver claims yet. The reply lists the devices bound,
+    /// with the pid of their driver or 0 when it was deferred
+    fn install_driver(
+        &mut self,
+        sender: u64,
+        name: String,
+        manifest: String,
+        image: Vec<u8>,
+        out: &mut Vec<u8>,
+    ) -> i32 {
+        // The MAC policy decides who may load which driver, by name
+        if let Err(status) = self.host.may_load(sender, &name) {
+            return status;
+        }
+        let matches = match drivers::parse_matches(&manifest) {
+            Ok(matches) if !matches.is_empty() => matches,
+            _ => return STATUS_EINVAL,
+        };
+        if SandboxManifest::parse(&manifest).is_err() || DriverDeps::parse(&manifest).is_err() {
+            return STATUS_EINVAL;
+        }
+
+        // Refused images are not kept; each binding is audited on its own
+        let verdict = self.trust.verify(&image);
+        if !self.policy.allows(&verdict) {
+            let record = format!(
+                "driver-install decision=deny verdict={} policy={} name={} sender={}",
+                verdict.as_str(),
+                self.policy.as_str(),
+                name,
+                sender
+            );
+            self.host.audit(AUDIT_DRIVER_LOAD, &record);
+            self.loads_denied += 1;
+            return STATUS_EKEYREJECTED;
+        }
+
+        let package = match self.packages.install(DriverPackage { name, matches, manifest, image }) {
+            Ok(package) => package,
+            Err(RegistryError::Exists) => return STATUS_EEXIST,
+            Err(RegistryError::Full) => return STATUS_ENOSPC,
+        };
+
+        let unclaimed: Vec<u64> = self
+            .devices
+            .iter()
+            .filter(|device| device.driver_pid.is_none() && package.drives(device.vendor_id, device.device_id))
+            .map(|device| device.handle)
+            .filter(|handle| !self.resolver.deferred().any(|load| load.load.device == *handle))
+            .collect();
+        let mut bound = Vec::new();
+        for handle in unclaimed {
+            let mut pid = Vec::new();
+            let status = self.load_driver(sender, handle, &package.name, &package.manifest, &package.image, &mut pid);
+            if status == STATUS_OK {
+                bound.extend_from_slice(&handle.to_le_bytes());
+                bound.extend_from_slice(&pid);
+            }
+        }
+        out.extend_from_slice(&((bound.len() / 16) as u32).to_le_bytes());
+        out.extend_from_slice(&bound);
+        STATUS_OK
+    }
+
+    /// Stop every instance of driver `name` and forget its package. Held
+    /// devices make it fail with EBUSY and the holds as payload, unless
+    /// forced: the holders are then told their device is revoked
+    fn unload_driver(&mut self, sender: u64, name: &str, force: bool, out: &mut Vec<u8>, outbox: &mut Outbox) -> i32 {
+        if let Err(status) = self.host.may_load(sender, name) {
+            return status;
+        }
+        let bound: Vec<usize> = (0..self.devices.len())
+            .filter(|&index| self.devices[index].driver_pid.is_some() && self.devices[index].driver == name)
+            .collect();
+        let deferred = self.resolver.deferred().any(|load| load.name == name);
+        if bound.is_empty() && !deferred && self.packages.get(name).is_none() {
+            return STATUS_ENOENT;
+        }
+
+        if !force {
+            let mut held = false;
+            for &index in bound.iter() {
+                for hold in self.holds.of(self.devices[index].handle) {
+                    encode_hold(hold.id, hold.device, hold.holder, &hold.purpose, out);
+                    held = true;
+                }
+            }
+            if held {
+                return STATUS_EBUSY;
+            }
+        }
+
+        self.packages.remove(name);
+        self.resolver.cancel(|load| load.name == name);
+        for &index in bound.iter() {
+            let handle = self.devices[index].handle;
+            let revoked = self.holds.revoke(handle);
+            for hold in revoked.iter() {
+                outbox.push((hold.holder, revoked_event(handle, hold.id)));
+            }
+
+            // Killing the driver fails the calls still queued on its ports
+            let device = &mut self.devices[index];
+            let pid = device.driver_pid.take().unwrap_or(0);
+            self.host.kill(pid);
+            device.driver = String::new();
+            let provides = core::mem::take(&mut device.provides);
+            let was_ready = core::mem::replace(&mut device.ready, false);
+            if was_ready {
+                self.withdraw(&provides);
+            }
+
+            let record = format!(
+                "driver-unload name={} device={:#x} pid={} forced={} holds={} sender={}",
+                name,
+                handle,
+                pid,
+                force,
+                revoked.len(),
+                sender
+            );
+            self.host.audit(AUDIT_DRIVER_UNLOAD, &record);
+        }
+
+        // Another installed driver may take the devices over
+        for &index in bound.iter() {
+            let device = &self.devices[index];
+            let (handle, vendor_id, device_id) = (device.handle, device.vendor_id, device.device_id);
+            if let Some(package) = self.packages.find(vendor_id, device_id) {
+                let _ =
+                    self.load_driver(sender, handle, &package.name, &package.manifest, &package.image, &mut Vec::new());
+            }
+        }
+        out.extend_from_slice(&(bound.len() as u32).to_le_bytes());
+        STATUS_OK
+    }
+
+    /// Facilities of a driver that went away stop being available, unless
+    /// another ready driver provides them too
+    fn withdraw(&mut self, facilities: &[String]) {
+        for facility in facilities.iter() {
+            let still_provided = self
+                .devices
+                .iter()
+                .any(|device| device.ready && device.provides.iter().any(|provided| provided == facility));
+            if !still_provided {
+                self.resolver.withdraw(facility);
+            }
+        }
+    }
+
+    fn hold_device(&mut self, sender: u64, handle: u64, purpose: &str, out: &mut Vec<u8>) -> i32 {
+        // Only a device being driven can be in use
+        if !self.devices.iter().any(|device| device.handle == handle && device.driver_pid.is_some()) {
+            return STATUS_ENOENT;
+        }
+        match self.holds.hold(handle, sender, purpose) {
+            Ok(id) => {
+                out.extend_from_slice(&id.to_le_bytes());
+                STATUS_OK
+            }
+            Err(_) => STATUS_ENOSPC,
+        }
+    }
+
+    /// Add a trust anchor stored in the keyring
+    fn enroll_key(&mut self, keyring_handle: u64, out: &mut Vec<u8>) -> i32 {
+        let public_key = match self.host.keyring_read(keyring_handle) {
+            Ok(public_key) => public_key,
+            Err(status) => return status,
+        };
+
+        match self.trust.enroll(&public_key) {
+            Ok(id) => {
+                out.extend_from_slice(&id);
+                STATUS_OK
+            }
+            Err(TrustError::Locked) => STATUS_EPERM,
+            Err(TrustError::Full) => STATUS_ENOSPC,
+            Err(TrustError::InvalidKey) => STATUS_EINVAL,
+        }
+    }
+
+    /// Keys a driver may accept firmware from: the driver signing keys, and
+    /// only once they are locked so that a driver never trusts a partial set
+    fn trust_anchors(&self, out: &mut Vec<u8>) -> i32 {
+        if !self.trust.is_locked() {
+            return STATUS_EBUSY;
+        }
+        out.extend_from_slice(&(self.trust.len() as u32).to_le_bytes());
+        for public_key in self.trust.public_keys() {
+            out.extend_from_slice(public_key);
+        }
+        STATUS_OK
+    }
+
+    /// Attach the sandbox profile described by `manifest` to `pid`
+    fn confine(&mut self, pid: u64, manifest: &str, device_windows: &[BusRange]) -> Result<(), i32> {
+        let manifest = SandboxManifest::parse(manifest).map_err(|_| STATUS_EINVAL)?;
+        let profile = manifest.to_profile(|name| self.host.lookup(name), device_windows).map_err(|_| STATUS_EINVAL)?;
+        self.host.sandbox_load(pid, &profile).map_err(|_| STATUS_EPERM)
+    }
+
+    fn set_policy(&mut self, sender: u64, policy: u32) -> i32 {
+        let policy = match Policy::from_u32(policy) {
+            Some(policy) => policy,
+            None => return STATUS_EINVAL,
+        };
+
+        // After lockdown the policy may only get stricter
+        if self.trust.is_locked() && policy == Policy::Permissive && self.policy == Policy::Enforcing {
+            return STATUS_EPERM;
+        }
+
+        let record = format!("driver-policy sender={} old={} new={}", sender, self.policy.as_str(), policy.as_str());
+        self.host.audit(AUDIT_DRIVER_POLICY, &record);
+
+        self.policy = policy;
+        STATUS_OK
+    }
+
+    fn audit_load(&mut self, sender: u64, index: usize, name: &str, verdict: &Verdict, allowed: bool) {
+        let key = match verdict.key_id() {
+            Some(id) => hex(&id),
+            None => String::from("none"),
+        };
+        // Most significant fields first, the kernel truncates long records
+        let device = &self.devices[index];
+        let record = format!(
+            "driver-load decision={} verdict={} policy={} name={} device={:#x} pci={:04x}:{:04x} key={} sender={}",
+            if allowed { "allow" } else { "deny" },
+            verdict.as_str(),
+            self.policy.as_str(),
+            name,
+            device.handle,
+            device.vendor_id,
+            device.device_id,
+            key,
+            sender,
+        );
+        self.host.audit(AUDIT_DRIVER_LOAD, &record);
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        text.push_str(&format!("{:02x}", byte));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Capability the tests present for administrative requests
+    const ADMIN: u64 = 7;
+
+    #[derive(Default)]
+    struct FakeHost {
+        spawned: Vec<(u64, String)>,
+        killed: Vec<u64>,
+        audits: Vec<(u32, String)>,
+    }
+
+    impl IoHost for FakeHost {
+        fn check_rights(&self, capability: u64, _rights: u64, _sender: u64) -> bool {
+            capability == ADMIN
+        }
+
+        fn grant(&mut self, _cap: u64, _pid: u64, _rights: u64) -> bool {
+            true
+        }
+
+        fn spawn_suspended(&mut self, name: &str, _image: &[u8]) -> Result<u64, i32> {
+            let pid = 100 + self.spawned.len() as u64;
+            self.spawned.push((pid, String::from(name)));
+            Ok(pid)
+        }
+
+        fn resume(&mut self, _pid: u64) -> Result<(), i32> {
+            Ok(())
+        }
+
+        fn kill(&mut self, pid: u64) {
+            self.killed.push(pid);
+        }
+
+        fn lookup(&self, _name: &str) -> Option<u64> {
+            Some(1)
+        }
+
+        fn sandbox_load(&mut self, _pid: u64, _profile: &[u8]) -> Result<(), i32> {
+            Ok(())
+        }
+
+        fn may_load(&self, _sender: u64, _name: &str) -> Result<(), i32> {
+            Ok(())
+        }
+
+        fn audit(&mut self, event: u32, record: &str) {
+            self.audits.push((event, String::from(record)));
+        }
+
+        fn keyring_read(&mut self, _handle: u64) -> Result<Vec<u8>, i32> {
+            Err(STATUS_ENOENT)
+        }
+    }
+
+    /// Send one request and return the status and payload of its reply
+    fn call(server: &mut IoServer<FakeHost>, sender: u64, request: &[u8]) -> (i32, Vec<u8>) {
+        let mut outbox = Outbox::new();
+        server.handle(sender, ADMIN, request, &mut outbox);
+        assert_eq!(outbox.len(), 1);
+        let (recipient, message) = outbox.remove(0);
+        assert_eq!(recipient, sender);
+        (i32::from_le_bytes(message[..4].try_into().unwrap()), message[4..].to_vec())
+    }
+
+    fn put_string(request: &mut Vec<u8>, text: &str) {
+        request.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        request.extend_from_slice(text.as_bytes());
+    }
+
+    /// A permissive server with a device registered for each handle
+    fn server(devices: &[u64]) -> IoServer<FakeHost> {
+        let mut server = IoServer::new(FakeHost::default());
+        for &device in devices {
+            let mut request = OP_REGISTER_DEVICE.to_le_bytes().to_vec();
+            request.extend_from_slice(&device.to_le_bytes());
+            request.extend_from_slice(&[0x86, 0x80, 0x0e, 0x10]);
+            request.resize(64, 0);
+            assert_eq!(call(&mut server, KERNEL_ENDPOINT, &request).0, STATUS_OK);
+        }
+        let mut request = OP_SET_POLICY.to_le_bytes().to_vec();
+        request.extend_from_slice(&Policy::Permissive.as_u32().to_le_bytes());
+        assert_eq!(call(&mut server, 1, &request).0, STATUS_OK);
+        server
+    }
+
+    fn load(server: &mut IoServer<FakeHost>, device: u64, name: &str, deps: &str) -> (i32, Vec<u8>) {
+        let mut request = OP_LOAD_DRIVER.to_le_bytes().to_vec();
+        request.extend_from_slice(&device.to_le_bytes());
+        put_string(&mut request, name);
+        put_string(&mut request, &format!("syscalls = process, ipc\nipc = io\n{}", deps));
+        request.extend_from_slice(b"image");
+        call(server, 1, &request)
+    }
+
+    fn provide(server: &mut IoServer<FakeHost>, name: &str) {
+        let mut request = OP_PROVIDE.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        assert_eq!(call(server, KERNEL_ENDPOINT, &request).0, STATUS_OK);
+    }
+
+    fn spawned(server: &IoServer<FakeHost>) -> Vec<&str> {
+        server.host.spawned.iter().map(|(_, name)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_starts_deferred_drivers_in_dependency_order() {
+        let mut server = server(&[0x10, 0x20]);
+
+        // Loaded before the NIC driver it needs, then the NIC driver before the bus
+        assert_eq!(load(&mut server, 0x20, "netstack", "requires = nic\nprovides = net"), (STATUS_OK, vec![0; 8]));
+        assert_eq!(load(&mut server, 0x10, "e1000", "requires = pci\nprovides = nic"), (STATUS_OK, vec![0; 8]));
+        assert!(spawned(&server).is_empty());
+        assert_eq!(call(&mut server, 1, &OP_LIST_DEFERRED.to_le_bytes()).1[..4], 2u32.to_le_bytes());
+
+        provide(&mut server, "pci");
+        assert_eq!(spawned(&server), ["e1000"]);
+
+        // What a started driver provides only counts once it is ready
+        let e1000 = server.host.spawned[0].0;
+        assert_eq!(call(&mut server, 1, &OP_LIST_DEFERRED.to_le_bytes()).1[..4], 1u32.to_le_bytes());
+        assert_eq!(call(&mut server, e1000, &OP_DRIVER_READY.to_le_bytes()).0, STATUS_OK);
+        assert_eq!(spawned(&server), ["e1000", "netstack"]);
+        assert_eq!(call(&mut server, 1, &OP_LIST_DEFERRED.to_le_bytes()), (STATUS_OK, vec![0; 4]));
+
+        // Each device now has its driver
+        let (status, devices) = call(&mut server, 1, &OP_LIST_DEVICES.to_le_bytes());
+        assert_eq!(status, STATUS_OK);
+        let mut expected = Vec::new();
+        encode_device(0x10, 0x8086, 0x100e, e1000, "e1000", &mut expected);
+        encode_device(0x20, 0x8086, 0x100e, server.host.spawned[1].0, "netstack", &mut expected);
+        assert_eq!(devices, expected);
+    }
+
+    #[test]
+    fn test_refuses_dependency_cycles() {
+        let mut server = server(&[0x10, 0x20, 0x30]);
+        provide(&mut server, "pci");
+        assert_eq!(load(&mut server, 0x10, "gpu", "requires = pci, dma\nprovides = gpu").0, STATUS_OK);
+        assert_eq!(load(&mut server, 0x20, "iommu", "requires = fence\nprovides = dma").0, STATUS_OK);
+
+        let (status, payload) = load(&mut server, 0x30, "fencer", "requires = gpu\nprovides = fence");
+        assert_eq!(status, STATUS_ELOOP);
+        let cycle = "fencer needs gpu from gpu, gpu needs dma from iommu, iommu needs fence from fencer";
+        assert_eq!(payload, cycle.as_bytes());
+        assert!(spawned(&server).is_empty());
+
+        let (event, record) = server.host.audits.last().unwrap();
+        assert_eq!(*event, AUDIT_DRIVER_DEPS);
+        assert_eq!(*record, format!("driver-deps cycle name=fencer device=0x30: {}", cycle));
+
+        // The refused driver is not kept waiting, the others are
+        assert_eq!(call(&mut server, 1, &OP_LIST_DEFERRED.to_le_bytes()).1[..4], 2u32.to_le_bytes());
+        assert_eq!(load(&mut server, 0x30, "fencer", "provides = fence"), (STATUS_OK, 100u64.to_le_bytes().to_vec()));
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ * Orion Operating System - Driver Signing
+ *
+ * Every driver binary carries a signature trailer appended at build time.
+ * The I/O server checks it against its trust store before the driver is
+ * started and handed its MMIO/DMA capabilities. Trust anchors are Ed25519
+ * public keys enrolled from the keyring during boot; once the store is
+ * locked it cannot change until the next boot.
+ *
+ * Trailer layout (last 84 bytes of the image, little-endian):
+ *
+ *   0   64  Ed25519 signature over every byte preceding the trailer
+ *   64   8  key id, the first 8 bytes of SHA-512(public key)
+ *   72   4  trailer version
+ *   76   8  magic "ORIONSIG"
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{self, PUBLIC_KEY_SIZE, SIGNATURE_SIZE};
+use orion_crypto::sha512::Sha512;
+
+pub const SIGNATURE_MAGIC: [u8; 8] = *b"ORIONSIG";
+pub const TRAILER_VERSION: u32 = 1;
+pub const TRAILER_SIZE: usize = SIGNATURE_SIZE + 8 + 4 + 8;
+
+/// Maximum number of enrolled trust anchors
+pub const MAX_TRUSTED_KEYS: usize = 16;
+
+pub type KeyId = [u8; 8];
+
+/// Identifier of a public key as recorded in signature trailers
+pub fn key_id(public_key: &[u8; PUBLIC_KEY_SIZE]) -> KeyId {
+    let digest = Sha512::digest(public_key);
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&digest[..8]);
+    id
+}
+
+/// A driver image split into its signed payload and its trailer
+pub struct SignedImage<'a> {
+    pub payload: &'a [u8],
+    pub key_id: KeyId,
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+/// Outcome of checking one driver image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Valid(KeyId),
+    Unsigned,
+    Malformed,
+    UnknownKey(KeyId),
+    BadSignature(KeyId),
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Valid(_) => "valid",
+            Verdict::Unsigned => "unsigned",
+            Verdict::Malformed => "malformed",
+            Verdict::UnknownKey(_) => "unknown-key",
+            Verdict::BadSignature(_) => "bad-signature",
+        }
+    }
+
+    pub fn key_id(&self) -> Option<KeyId> {
+        match *self {
+            Verdict::Valid(id) | Verdict::UnknownKey(id) | Verdict::BadSignature(id) => Some(id),
+            Verdict::Unsigned | Verdict::Malformed => None,
+        }
+    }
+}
+
+/// What happens to drivers that fail verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Only validly signed drivers are started
+    Enforcing,
+    /// Every driver is started, failures are only audited
+    Permissive,
+}
+
+impl Policy {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Policy::Enforcing),
+            1 => Some(Policy::Permissive),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            Policy::Enforcing => 0,
+            Policy::Permissive => 1,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Policy::Enforcing => "enforcing",
+            Policy::Permissive => "permissive",
+        }
+    }
+
+    pub fn allows(&self, verdict: &Verdict) -> bool {
+        matches!(verdict, Verdict::Valid(_)) || *self == Policy::Permissive
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustError {
+    Locked,
+    Full,
+    InvalidKey,
+}
+
+impl<'a> SignedImage<'a> {
+    /// Split an image; `Ok(None)` means it has no trailer at all
+    pub fn parse(image: &'a [u8]) -> Result<Option<Self>, Verdict> {
+        if image.len() < TRAILER_SIZE || image[image.len() - 8..] != SIGNATURE_MAGIC {
+            return Ok(None);
+        }
+
+        let (payload, trailer) = image.split_at(image.len() - TRAILER_SIZE);
+        let version = u32::from_le_bytes([trailer[72], trailer[73], trailer[74], trailer[75]]);
+        if version != TRAILER_VERSION {
+            return Err(Verdict::Malformed);
+        }
+
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        signature.copy_from_slice(&trailer[..SIGNATURE_SIZE]);
+        let mut key_id = [0u8; 8];
+        key_id.copy_from_slice(&trailer[64..72]);
+
+        Ok(Some(Self { payload, key_id, signature }))
+    }
+}
+
+struct TrustedKey {
+    id: KeyId,
+    public_key: [u8; PUBLIC_KEY_SIZE],
+}
+
+/// Public keys allowed to sign drivers
+pub struct TrustStore {
+    keys: Vec<TrustedKey>,
+    locked: bool,
+}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            locked: false,
+        }
+    }
+
+    pub fn enroll(&mut self, public_key: &[u8]) -> Result<KeyId, TrustError> {
+        if self.locked {
+            return Err(TrustError::Locked);
+        }
+        let public_key: [u8; PUBLIC_KEY_SIZE] = public_key.try_into().map_err(|_| TrustError::InvalidKey)?;
+
+        let id = key_id(&public_key);
+        if self.keys.iter().any(|key| key.id == id) {
+            return Ok(id);
+        }
+        if self.keys.len() >= MAX_TRUSTED_KEYS {
+            return Err(TrustError::Full);
+        }
+
+        self.keys.push(TrustedKey { id, public_key });
+        Ok(id)
+    }
+
+    /// Freeze the store until the next boot
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Enrolled public keys, in enrollment order
+    pub fn public_keys(&self) -> impl Iterator<Item = &[u8; PUBLIC_KEY_SIZE]> {
+        self.keys.iter().map(|key| &key.public_key)
+    }
+
+    pub fn verify(&self, image: &[u8]) -> Verdict {
+        let signed = match SignedImage::parse(image) {
+            Ok(Some(signed)) => signed,
+            Ok(None) => return Verdict::Unsigned,
+            Err(verdict) => return verdict,
+        };
+
+        let key = match self.keys.iter().find(|key| key.id == signed.key_id) {
+            Some(key) => key,
+            None => return Verdict::UnknownKey(signed.key_id),
+        };
+
+        if ed25519::verify(&key.public_key, signed.payload, &signed.signature) {
+            Verdict::Valid(signed.key_id)
+        } else {
+            Verdict::BadSignature(signed.key_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 8032 section 7.1, test 2: public key and signature of 0x72
+    fn rfc8032_test2() -> ([u8; 32], [u8; 64]) {
+        let hex = |text: &str| -> Vec<u8> {
+            (0..text.len() / 2)
+                .map(|i| u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).unwrap())
+                .collect()
+        };
+        let public_key = hex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c");
+        let signature = hex("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+                             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00");
+        (public_key.try_into().unwrap(), signature.try_into().unwrap())
+    }
+
+    fn signed_image(payload: &[u8], public_key: &[u8; 32], signature: &[u8; 64]) -> Vec<u8> {
+        let mut image = payload.to_vec();
+        image.extend_from_slice(signature);
+        image.extend_from_slice(&key_id(public_key));
+        image.extend_from_slice(&TRAILER_VERSION.to_le_bytes());
+        image.extend_from_slice(&SIGNATURE_MAGIC);
+        image
+    }
+
+    #[test]
+    fn test_verifies_against_enrolled_keys() {
+        let (public_key, signature) = rfc8032_test2();
+        let image = signed_image(&[0x72], &public_key, &signature);
+
+        let mut store = TrustStore::new();
+        assert_eq!(store.verify(&image), Verdict::UnknownKey(key_id(&public_key)));
+
+        assert_eq!(store.enroll(&public_key), Ok(key_id(&public_key)));
+        assert_eq!(store.verify(&image), Verdict::Valid(key_id(&public_key)));
+
+        let mut tampered = image.clone();
+        tampered[0] ^= 0x01;
+        assert_eq!(store.verify(&tampered), Verdict::BadSignature(key_id(&public_key)));
+
+        assert_eq!(store.verify(b"\x7fELF unsigned driver"), Verdict::Unsigned);
+
+        let mut future = image.clone();
+        let version_offset = future.len() - 12;
+        future[version_offset] = 2;
+        assert_eq!(store.verify(&future), Verdict::Malformed);
+    }
+
+    #[test]
+    fn test_locked_store_and_policies() {
+        let (public_key, _) = rfc8032_test2();
+        let mut store = TrustStore::new();
+        store.lock();
+        assert_eq!(store.enroll(&public_key), Err(TrustError::Locked));
+        assert_eq!(TrustStore::new().enroll(&public_key[..31]), Err(TrustError::InvalidKey));
+
+        assert!(Policy::Enforcing.allows(&Verdict::Valid([0; 8])));
+        assert!(!Policy::Enforcing.allows(&Verdict::Unsigned));
+        assert!(Policy::Permissive.allows(&Verdict::BadSignature([0; 8])));
+    }
+}
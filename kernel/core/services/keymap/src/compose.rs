@@ -0,0 +1,265 @@
+/*
+ * Orion Operating System - Keyboard State and Composition
+ *
+ * Tracks the modifiers of the physical keyboard and turns key presses into
+ * keysyms and text for a layout. Shift and AltGr pick the shift level;
+ * Caps Lock toggles case on letter keys only. Dead keys produce no text
+ * and combine with the next key: a base letter that has an accented form
+ * yields it, Space or the dead key itself yields the accent alone, and
+ * anything else yields the accent followed by that key's character.
+ * Ctrl and Alt chords produce keysyms but no text.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::layout::*;
+
+// Modifier mask reported with each key event
+pub const MOD_SHIFT: u32 = 1 << 0;
+pub const MOD_CAPS_LOCK: u32 = 1 << 1;
+pub const MOD_CTRL: u32 = 1 << 2;
+pub const MOD_ALT: u32 = 1 << 3;
+pub const MOD_ALTGR: u32 = 1 << 4;
+pub const MOD_SUPER: u32 = 1 << 5;
+
+/// Longest text one key press can produce (accent plus character)
+pub const MAX_TEXT: usize = 8;
+
+// Dead key, its spacing accent, and the letters it combines with
+const DEAD_KEYS: [(u32, char, &str, &str); 5] = [
+    (XK_DEAD_GRAVE, '`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    (XK_DEAD_ACUTE, '´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    (XK_DEAD_CIRCUMFLEX, '^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    (XK_DEAD_TILDE, '~', "anoANO", "ãñõÃÑÕ"),
+    (XK_DEAD_DIAERESIS, '¨', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+];
+
+fn dead_key(keysym: u32) -> Option<&'static (u32, char, &'static str, &'static str)> {
+    DEAD_KEYS.iter().find(|(dead, ..)| *dead == keysym)
+}
+
+/// Accented form of `base` under the dead key `dead`
+fn combine(dead: u32, base: char) -> Option<char> {
+    let (_, _, bases, results) = dead_key(dead)?;
+    let index = bases.chars().position(|ch| ch == base)?;
+    results.chars().nth(index)
+}
+
+// Keys whose Shift level is the upper case of their plain level
+fn is_letter(plain: u32, shifted: u32) -> bool {
+    match (keysym_to_char(plain), keysym_to_char(shifted)) {
+        (Some(lower), Some(upper)) => lower.is_lowercase() && lower.to_uppercase().eq(core::iter::once(upper)),
+        _ => false,
+    }
+}
+
+/// Text produced by one key press, UTF-8 encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Text {
+    bytes: [u8; MAX_TEXT],
+    len: usize,
+}
+
+impl Text {
+    fn new() -> Self {
+        Self { bytes: [0; MAX_TEXT], len: 0 }
+    }
+
+    fn push(&mut self, ch: char) {
+        self.len += ch.encode_utf8(&mut self.bytes[self.len..]).len();
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Translation of one key event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Translation {
+    pub keycode: u32,
+    pub keysym: u32,
+    pub modifiers: u32,
+    pub down: bool,
+    pub text: Text,
+}
+
+#[derive(Debug, Default)]
+pub struct KeyboardState {
+    shift: u8,
+    ctrl: u8,
+    alt: u8,
+    altgr: bool,
+    super_key: u8,
+    caps_lock: bool,
+    dead: Option<u32>,
+}
+
+impl KeyboardState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn modifiers(&self) -> u32 {
+        let mut mask = 0;
+        for (active, bit) in [
+            (self.shift != 0, MOD_SHIFT),
+            (self.caps_lock, MOD_CAPS_LOCK),
+            (self.ctrl != 0, MOD_CTRL),
+            (self.alt != 0, MOD_ALT),
+            (self.altgr, MOD_ALTGR),
+            (self.super_key != 0, MOD_SUPER),
+        ] {
+            if active {
+                mask |= bit;
+            }
+        }
+        mask
+    }
+
+    /// Forget a pending dead key, e.g. when the focus moves
+    pub fn cancel_dead_key(&mut self) {
+        self.dead = None;
+    }
+
+    // Left and right keys of a modifier are counted so releasing one keeps
+    // the other effective
+    fn track_modifier(&mut self, layout: Layout, keycode: u32, down: bool) -> bool {
+        let counter = match keycode {
+            KEY_LEFT_SHIFT | KEY_RIGHT_SHIFT => &mut self.shift,
+            KEY_LEFT_CTRL | KEY_RIGHT_CTRL => &mut self.ctrl,
+            KEY_LEFT_ALT => &mut self.alt,
+            KEY_RIGHT_ALT if layout.has_altgr() => {
+                self.altgr = down;
+                return true;
+            }
+            KEY_RIGHT_ALT => &mut self.alt,
+            KEY_LEFT_SUPER | KEY_RIGHT_SUPER => &mut self.super_key,
+            KEY_CAPS_LOCK => {
+                if down {
+                    self.caps_lock = !self.caps_lock;
+                }
+                return true;
+            }
+            _ => return false,
+        };
+        *counter = if down { counter.saturating_add(1) } else { counter.saturating_sub(1) };
+        true
+    }
+
+    /// Translate a key event of the physical keyboard with `layout`
+    pub fn key(&mut self, layout: Layout, keycode: u32, down: bool) -> Translation {
+        let is_modifier = self.track_modifier(layout, keycode, down);
+        let mut level = (self.shift != 0) as usize | if self.altgr { 2 } else { 0 };
+        if self.caps_lock && is_letter(layout.keysym(keycode, 0), layout.keysym(keycode, 1)) {
+            level ^= 1;
+        }
+        let keysym = layout.keysym(keycode, level);
+        let mut translation = Translation { keycode, keysym, modifiers: self.modifiers(), down, text: Text::new() };
+        if !down || is_modifier || keysym == 0 {
+            return translation;
+        }
+        if self.ctrl != 0 || self.alt != 0 {
+            self.dead = None;
+            return translation;
+        }
+
+        let typed = keysym_to_char(keysym);
+        match (self.dead.take(), dead_key(keysym)) {
+            // Dead key after dead key: the first accent alone, the second stays pending
+            (Some(pending), Some(_)) if pending != keysym => {
+                translation.text.push(dead_key(pending).map_or(' ', |entry| entry.1));
+                self.dead = Some(keysym);
+            }
+            (Some(_), Some(&(_, accent, ..))) => translation.text.push(accent),
+            (None, Some(_)) => self.dead = Some(keysym),
+            (Some(pending), None) => {
+                let accent = dead_key(pending).map_or(' ', |entry| entry.1);
+                match typed {
+                    Some(' ') => translation.text.push(accent),
+                    Some(ch) => match combine(pending, ch) {
+                        Some(accented) => translation.text.push(accented),
+                        None => {
+                            translation.text.push(accent);
+                            translation.text.push(ch);
+                        }
+                    },
+                    // Keys without text (cursor keys...) drop the accent
+                    None => {}
+                }
+            }
+            (None, None) => {
+                if let Some(ch) = typed {
+                    translation.text.push(ch);
+                }
+            }
+        }
+        translation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(state: &mut KeyboardState, layout: Layout, keycode: u32) -> alloc::string::String {
+        let translation = state.key(layout, keycode, true);
+        state.key(layout, keycode, false);
+        alloc::string::String::from(core::str::from_utf8(translation.text.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_applies_modifiers_and_caps_lock() {
+        let mut state = KeyboardState::new();
+        assert_eq!(typed(&mut state, Layout::Us, 0x04), "a");
+        state.key(Layout::Us, KEY_LEFT_SHIFT, true);
+        assert_eq!(state.modifiers(), MOD_SHIFT);
+        assert_eq!(typed(&mut state, Layout::Us, 0x1f), "@");
+        state.key(Layout::Us, KEY_LEFT_SHIFT, false);
+
+        // Caps Lock upper-cases letters (ü on a German keyboard) but not digits
+        typed(&mut state, Layout::De, KEY_CAPS_LOCK);
+        assert_eq!(typed(&mut state, Layout::De, 0x2f), "Ü");
+        assert_eq!(typed(&mut state, Layout::De, 0x1e), "1");
+        typed(&mut state, Layout::De, KEY_CAPS_LOCK);
+
+        // AltGr on the right Alt key, Ctrl chords produce no text
+        state.key(Layout::Fr, KEY_RIGHT_ALT, true);
+        assert_eq!(typed(&mut state, Layout::Fr, 0x08), "€");
+        state.key(Layout::Fr, KEY_RIGHT_ALT, false);
+        state.key(Layout::Fr, KEY_LEFT_CTRL, true);
+        let translation = state.key(Layout::Fr, 0x06, true);
+        assert_eq!(translation.keysym, 'c' as u32);
+        assert!(translation.text.is_empty());
+        assert_eq!(translation.modifiers, MOD_CTRL);
+    }
+
+    #[test]
+    fn test_composes_dead_keys() {
+        let mut state = KeyboardState::new();
+        // French circumflex key, then e
+        assert_eq!(typed(&mut state, Layout::Fr, 0x2f), "");
+        assert_eq!(typed(&mut state, Layout::Fr, 0x08), "ê");
+        // Shift on the dead key gives the diaeresis
+        state.key(Layout::Fr, KEY_LEFT_SHIFT, true);
+        assert_eq!(typed(&mut state, Layout::Fr, 0x2f), "");
+        state.key(Layout::Fr, KEY_LEFT_SHIFT, false);
+        assert_eq!(typed(&mut state, Layout::Fr, 0x0c), "ï");
+        // No accented form: accent then the character
+        assert_eq!(typed(&mut state, Layout::Fr, 0x2f), "");
+        assert_eq!(typed(&mut state, Layout::Fr, 0x1c), "^y");
+        // Space or a second press gives the accent alone
+        assert_eq!(typed(&mut state, Layout::De, 0x2e), "");
+        assert_eq!(typed(&mut state, Layout::De, 0x2c), "´");
+        assert_eq!(typed(&mut state, Layout::De, 0x2e), "");
+        assert_eq!(typed(&mut state, Layout::De, 0x2e), "´");
+    }
+}
@@ -0,0 +1,340 @@
+/*
+ * Orion Operating System - Keyboard Layouts
+ *
+ * Keycodes are USB HID usages of the keyboard page (0x07), which every
+ * input driver can produce: USB keyboards report them directly and PS/2
+ * set 1 scancodes have a fixed translation. A layout gives each keycode up
+ * to four X11 keysyms, one per shift level (plain, Shift, AltGr,
+ * Shift+AltGr). Keys that do not depend on the layout (editing, cursor,
+ * function and modifier keys) are shared by all layouts.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+// Keysyms outside the Latin-1 range
+pub const XK_BACKSPACE: u32 = 0xff08;
+pub const XK_TAB: u32 = 0xff09;
+pub const XK_RETURN: u32 = 0xff0d;
+pub const XK_ESCAPE: u32 = 0xff1b;
+pub const XK_DELETE: u32 = 0xffff;
+pub const XK_HOME: u32 = 0xff50;
+pub const XK_LEFT: u32 = 0xff51;
+pub const XK_UP: u32 = 0xff52;
+pub const XK_RIGHT: u32 = 0xff53;
+pub const XK_DOWN: u32 = 0xff54;
+pub const XK_PAGE_UP: u32 = 0xff55;
+pub const XK_PAGE_DOWN: u32 = 0xff56;
+pub const XK_END: u32 = 0xff57;
+pub const XK_INSERT: u32 = 0xff63;
+pub const XK_KP_ENTER: u32 = 0xff8d;
+pub const XK_F1: u32 = 0xffbe;
+pub const XK_SHIFT_L: u32 = 0xffe1;
+pub const XK_SHIFT_R: u32 = 0xffe2;
+pub const XK_CONTROL_L: u32 = 0xffe3;
+pub const XK_CONTROL_R: u32 = 0xffe4;
+pub const XK_CAPS_LOCK: u32 = 0xffe5;
+pub const XK_ALT_L: u32 = 0xffe9;
+pub const XK_ALT_R: u32 = 0xffea;
+pub const XK_SUPER_L: u32 = 0xffeb;
+pub const XK_SUPER_R: u32 = 0xffec;
+pub const XK_ISO_LEVEL3_SHIFT: u32 = 0xfe03;
+pub const XK_DEAD_GRAVE: u32 = 0xfe50;
+pub const XK_DEAD_ACUTE: u32 = 0xfe51;
+pub const XK_DEAD_CIRCUMFLEX: u32 = 0xfe52;
+pub const XK_DEAD_TILDE: u32 = 0xfe53;
+pub const XK_DEAD_DIAERESIS: u32 = 0xfe57;
+pub const XK_EURO_SIGN: u32 = 0x20ac;
+
+// Keycodes with a special role
+pub const KEY_CAPS_LOCK: u32 = 0x39;
+pub const KEY_LEFT_CTRL: u32 = 0xe0;
+pub const KEY_LEFT_SHIFT: u32 = 0xe1;
+pub const KEY_LEFT_ALT: u32 = 0xe2;
+pub const KEY_LEFT_SUPER: u32 = 0xe3;
+pub const KEY_RIGHT_CTRL: u32 = 0xe4;
+pub const KEY_RIGHT_SHIFT: u32 = 0xe5;
+pub const KEY_RIGHT_ALT: u32 = 0xe6;
+pub const KEY_RIGHT_SUPER: u32 = 0xe7;
+
+/// Highest keycode the service accepts
+pub const MAX_KEYCODE: u32 = 0xff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layout {
+    Us,
+    Uk,
+    Fr,
+    De,
+}
+
+pub const LAYOUTS: [Layout; 4] = [Layout::Us, Layout::Uk, Layout::Fr, Layout::De];
+
+type Keys = &'static [(u8, [u32; 4])];
+
+const fn c(ch: char) -> u32 {
+    ch as u32
+}
+
+// Letters that sit at the same place in every layout below
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
+
+const US_KEYS: Keys = &[
+    (0x1e, [c('1'), c('!'), 0, 0]),
+    (0x1f, [c('2'), c('@'), 0, 0]),
+    (0x20, [c('3'), c('#'), 0, 0]),
+    (0x21, [c('4'), c('$'), 0, 0]),
+    (0x22, [c('5'), c('%'), 0, 0]),
+    (0x23, [c('6'), c('^'), 0, 0]),
+    (0x24, [c('7'), c('&'), 0, 0]),
+    (0x25, [c('8'), c('*'), 0, 0]),
+    (0x26, [c('9'), c('('), 0, 0]),
+    (0x27, [c('0'), c(')'), 0, 0]),
+    (0x2d, [c('-'), c('_'), 0, 0]),
+    (0x2e, [c('='), c('+'), 0, 0]),
+    (0x2f, [c('['), c('{'), 0, 0]),
+    (0x30, [c(']'), c('}'), 0, 0]),
+    (0x31, [c('\\'), c('|'), 0, 0]),
+    (0x32, [c('\\'), c('|'), 0, 0]),
+    (0x33, [c(';'), c(':'), 0, 0]),
+    (0x34, [c('\''), c('"'), 0, 0]),
+    (0x35, [c('`'), c('~'), 0, 0]),
+    (0x36, [c(','), c('<'), 0, 0]),
+    (0x37, [c('.'), c('>'), 0, 0]),
+    (0x38, [c('/'), c('?'), 0, 0]),
+    (0x64, [c('\\'), c('|'), 0, 0]),
+];
+
+const UK_KEYS: Keys = &[
+    (0x08, [c('e'), c('E'), c('é'), c('É')]),
+    (0x18, [c('u'), c('U'), c('ú'), c('Ú')]),
+    (0x0c, [c('i'), c('I'), c('í'), c('Í')]),
+    (0x12, [c('o'), c('O'), c('ó'), c('Ó')]),
+    (0x04, [c('a'), c('A'), c('á'), c('Á')]),
+    (0x1e, [c('1'), c('!'), 0, 0]),
+    (0x1f, [c('2'), c('"'), 0, 0]),
+    (0x20, [c('3'), c('£'), 0, 0]),
+    (0x21, [c('4'), c('$'), XK_EURO_SIGN, 0]),
+    (0x22, [c('5'), c('%'), 0, 0]),
+    (0x23, [c('6'), c('^'), 0, 0]),
+    (0x24, [c('7'), c('&'), 0, 0]),
+    (0x25, [c('8'), c('*'), 0, 0]),
+    (0x26, [c('9'), c('('), 0, 0]),
+    (0x27, [c('0'), c(')'), 0, 0]),
+    (0x2d, [c('-'), c('_'), 0, 0]),
+    (0x2e, [c('='), c('+'), 0, 0]),
+    (0x2f, [c('['), c('{'), 0, 0]),
+    (0x30, [c(']'), c('}'), 0, 0]),
+    (0x31, [c('#'), c('~'), 0, 0]),
+    (0x32, [c('#'), c('~'), 0, 0]),
+    (0x33, [c(';'), c(':'), 0, 0]),
+    (0x34, [c('\''), c('@'), 0, 0]),
+    (0x35, [c('`'), c('¬'), c('¦'), 0]),
+    (0x36, [c(','), c('<'), 0, 0]),
+    (0x37, [c('.'), c('>'), 0, 0]),
+    (0x38, [c('/'), c('?'), 0, 0]),
+    (0x64, [c('\\'), c('|'), 0, 0]),
+];
+
+const FR_KEYS: Keys = &[
+    (0x04, [c('q'), c('Q'), 0, 0]),
+    (0x14, [c('a'), c('A'), 0, 0]),
+    (0x1a, [c('z'), c('Z'), 0, 0]),
+    (0x1d, [c('w'), c('W'), 0, 0]),
+    (0x33, [c('m'), c('M'), 0, 0]),
+    (0x08, [c('e'), c('E'), XK_EURO_SIGN, 0]),
+    (0x10, [c(','), c('?'), 0, 0]),
+    (0x1e, [c('&'), c('1'), 0, 0]),
+    (0x1f, [c('é'), c('2'), XK_DEAD_TILDE, 0]),
+    (0x20, [c('"'), c('3'), c('#'), 0]),
+    (0x21, [c('\''), c('4'), c('{'), 0]),
+    (0x22, [c('('), c('5'), c('['), 0]),
+    (0x23, [c('-'), c('6'), c('|'), 0]),
+    (0x24, [c('è'), c('7'), XK_DEAD_GRAVE, 0]),
+    (0x25, [c('_'), c('8'), c('\\'), 0]),
+    (0x26, [c('ç'), c('9'), c('^'), 0]),
+    (0x27, [c('à'), c('0'), c('@'), 0]),
+    (0x2d, [c(')'), c('°'), c(']'), 0]),
+    (0x2e, [c('='), c('+'), c('}'), 0]),
+    (0x2f, [XK_DEAD_CIRCUMFLEX, XK_DEAD_DIAERESIS, 0, 0]),
+    (0x30, [c('$'), c('£'), c('¤'), 0]),
+    (0x31, [c('*'), c('µ'), 0, 0]),
+    (0x32, [c('*'), c('µ'), 0, 0]),
+    (0x34, [c('ù'), c('%'), 0, 0]),
+    (0x35, [c('²'), 0, 0, 0]),
+    (0x36, [c(';'), c('.'), 0, 0]),
+    (0x37, [c(':'), c('/'), 0, 0]),
+    (0x38, [c('!'), c('§'), 0, 0]),
+    (0x64, [c('<'), c('>'), 0, 0]),
+];
+
+const DE_KEYS: Keys = &[
+    (0x1c, [c('z'), c('Z'), 0, 0]),
+    (0x1d, [c('y'), c('Y'), 0, 0]),
+    (0x14, [c('q'), c('Q'), c('@'), 0]),
+    (0x08, [c('e'), c('E'), XK_EURO_SIGN, 0]),
+    (0x10, [c('m'), c('M'), c('µ'), 0]),
+    (0x1e, [c('1'), c('!'), 0, 0]),
+    (0x1f, [c('2'), c('"'), c('²'), 0]),
+    (0x20, [c('3'), c('§'), c('³'), 0]),
+    (0x21, [c('4'), c('$'), 0, 0]),
+    (0x22, [c('5'), c('%'), 0, 0]),
+    (0x23, [c('6'), c('&'), 0, 0]),
+    (0x24, [c('7'), c('/'), c('{'), 0]),
+    (0x25, [c('8'), c('('), c('['), 0]),
+    (0x26, [c('9'), c(')'), c(']'), 0]),
+    (0x27, [c('0'), c('='), c('}'), 0]),
+    (0x2d, [c('ß'), c('?'), c('\\'), 0]),
+    (0x2e, [XK_DEAD_ACUTE, XK_DEAD_GRAVE, 0, 0]),
+    (0x2f, [c('ü'), c('Ü'), 0, 0]),
+    (0x30, [c('+'), c('*'), c('~'), 0]),
+    (0x31, [c('#'), c('\''), 0, 0]),
+    (0x32, [c('#'), c('\''), 0, 0]),
+    (0x33, [c('ö'), c('Ö'), 0, 0]),
+    (0x34, [c('ä'), c('Ä'), 0, 0]),
+    (0x35, [XK_DEAD_CIRCUMFLEX, c('°'), 0, 0]),
+    (0x36, [c(','), c(';'), 0, 0]),
+    (0x37, [c('.'), c(':'), 0, 0]),
+    (0x38, [c('-'), c('_'), 0, 0]),
+    (0x64, [c('<'), c('>'), c('|'), 0]),
+];
+
+impl Layout {
+    pub fn id(self) -> u32 {
+        match self {
+            Layout::Us => 1,
+            Layout::Uk => 2,
+            Layout::Fr => 3,
+            Layout::De => 4,
+        }
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        LAYOUTS.iter().copied().find(|layout| layout.id() == id)
+    }
+
+    /// Short name, as used in configuration ("us", "gb", "fr", "de")
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "gb",
+            Layout::Fr => "fr",
+            Layout::De => "de",
+        }
+    }
+
+    /// Whether the right Alt key selects the third level
+    pub fn has_altgr(self) -> bool {
+        self != Layout::Us
+    }
+
+    fn keys(self) -> Keys {
+        match self {
+            Layout::Us => US_KEYS,
+            Layout::Uk => UK_KEYS,
+            Layout::Fr => FR_KEYS,
+            Layout::De => DE_KEYS,
+        }
+    }
+
+    /// Keysym of `keycode` at `level` (0 plain, 1 Shift, 2 AltGr,
+    /// 3 Shift+AltGr), or 0 when the key produces nothing there
+    pub fn keysym(self, keycode: u32, level: usize) -> u32 {
+        if let Some(keysym) = fixed_keysym(self, keycode) {
+            return keysym;
+        }
+        let levels = match self.keys().iter().find(|(key, _)| *key as u32 == keycode) {
+            Some((_, levels)) => *levels,
+            None => match keycode {
+                0x04..=0x1d => {
+                    let letter = LETTERS.as_bytes()[(keycode - 0x04) as usize] as u32;
+                    [letter, letter - 0x20, 0, 0]
+                }
+                0x2c => [c(' '), c(' '), c(' '), c(' ')],
+                _ => return 0,
+            },
+        };
+        match levels[level & 3] {
+            // An unset Shift+AltGr level falls back to AltGr
+            0 if level == 3 => levels[2],
+            keysym => keysym,
+        }
+    }
+}
+
+// Keys whose keysym does not depend on the shift level
+fn fixed_keysym(layout: Layout, keycode: u32) -> Option<u32> {
+    let keysym = match keycode {
+        0x28 => XK_RETURN,
+        0x29 => XK_ESCAPE,
+        0x2a => XK_BACKSPACE,
+        0x2b => XK_TAB,
+        KEY_CAPS_LOCK => XK_CAPS_LOCK,
+        0x3a..=0x45 => XK_F1 + (keycode - 0x3a),
+        0x49 => XK_INSERT,
+        0x4a => XK_HOME,
+        0x4b => XK_PAGE_UP,
+        0x4c => XK_DELETE,
+        0x4d => XK_END,
+        0x4e => XK_PAGE_DOWN,
+        0x4f => XK_RIGHT,
+        0x50 => XK_LEFT,
+        0x51 => XK_DOWN,
+        0x52 => XK_UP,
+        0x58 => XK_KP_ENTER,
+        KEY_LEFT_CTRL => XK_CONTROL_L,
+        KEY_LEFT_SHIFT => XK_SHIFT_L,
+        KEY_LEFT_ALT => XK_ALT_L,
+        KEY_LEFT_SUPER => XK_SUPER_L,
+        KEY_RIGHT_CTRL => XK_CONTROL_R,
+        KEY_RIGHT_SHIFT => XK_SHIFT_R,
+        KEY_RIGHT_ALT if layout.has_altgr() => XK_ISO_LEVEL3_SHIFT,
+        KEY_RIGHT_ALT => XK_ALT_R,
+        KEY_RIGHT_SUPER => XK_SUPER_R,
+        _ => return None,
+    };
+    Some(keysym)
+}
+
+/// Character a keysym types, if any
+pub fn keysym_to_char(keysym: u32) -> Option<char> {
+    match keysym {
+        0x20..=0x7e | 0xa0..=0xff | XK_EURO_SIGN => char::from_u32(keysym),
+        0x0100_0100..=0x0110_ffff => char::from_u32(keysym & 0x00ff_ffff),
+        XK_RETURN | XK_KP_ENTER => Some('\n'),
+        XK_TAB => Some('\t'),
+        XK_BACKSPACE => Some('\u{8}'),
+        XK_ESCAPE => Some('\u{1b}'),
+        XK_DELETE => Some('\u{7f}'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolves_levels_per_layout() {
+        // The key labelled Q on a US keyboard
+        assert_eq!(Layout::Us.keysym(0x14, 0), c('q'));
+        assert_eq!(Layout::Fr.keysym(0x14, 0), c('a'));
+        assert_eq!(Layout::De.keysym(0x14, 2), c('@'));
+        assert_eq!(Layout::Uk.keysym(0x20, 1), c('£'));
+        assert_eq!(Layout::De.keysym(0x1d, 1), c('Y'));
+        assert_eq!(Layout::Fr.keysym(0x1f, 2), XK_DEAD_TILDE);
+        assert_eq!(Layout::Fr.keysym(0x08, 3), XK_EURO_SIGN);
+        assert_eq!(Layout::Us.keysym(0x1f, 2), 0);
+        assert_eq!(Layout::Us.keysym(KEY_RIGHT_ALT, 0), XK_ALT_R);
+        assert_eq!(Layout::De.keysym(KEY_RIGHT_ALT, 0), XK_ISO_LEVEL3_SHIFT);
+        assert_eq!(Layout::Us.keysym(0x3b, 1), XK_F1 + 1);
+
+        assert_eq!(keysym_to_char(c('é')), Some('é'));
+        assert_eq!(keysym_to_char(XK_EURO_SIGN), Some('€'));
+        assert_eq!(keysym_to_char(XK_DEAD_ACUTE), None);
+        assert_eq!(Layout::from_id(Layout::De.id()), Some(Layout::De));
+    }
+}
@@ -0,0 +1,249 @@
+/*
+ * Orion Operating System - Keymap Server
+ *
+ * Turns the keycodes reported by keyboard drivers into keysyms and text.
+ * Each virtual terminal and display window may have its own layout (US,
+ * UK, French or German), falling back to the default one; the layout of
+ * the focused target is applied, with modifiers and dead keys composed
+ * into UTF-8 (see compose.rs). The owner of the focused target can ask for
+ * the raw key events, the text, or both, which lets games read physical
+ * keys while text fields get composed characters. Keysyms keep flowing to
+ * the display server as before.
+ *
+ * Reporting keys and focus needs the admin right on the keymap
+ * capability, changing layouts the write right, and reading layouts or
+ * subscribing the read right.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod compose;
+mod layout;
+mod protocol;
+
+use compose::{KeyboardState, Translation};
+use layout::{Layout, LAYOUTS, MAX_KEYCODE};
+use protocol::*;
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 2_000_000;
+
+/// Targets with a layout of their own
+const MAX_LAYOUTS: usize = 256;
+/// Subscriptions over all targets
+const MAX_SUBSCRIPTIONS: usize = 256;
+
+// Display server request (see services/display/src/protocol.rs)
+const DISPLAY_OP_INPUT_KEY: u32 = 14;
+
+struct KeymapServer {
+    keyboard: KeyboardState,
+    default_layout: Layout,
+    layouts: BTreeMap<Target, Layout>,
+    /// Focused target and the endpoint owning it
+    focus: Target,
+    focus_owner: u64,
+    /// (target, endpoint) -> subscription flags
+    subscriptions: BTreeMap<(Target, u64), u32>,
+    display: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl KeymapServer {
+    fn new() -> Self {
+        Self {
+            keyboard: KeyboardState::new(),
+            default_layout: Layout::Us,
+            layouts: BTreeMap::new(),
+            focus: Target::Default,
+            focus_owner: 0,
+            subscriptions: BTreeMap::new(),
+            display: IpcChannel::connect("display"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn layout_of(&self, target: Target) -> Layout {
+        self.layouts.get(&target).copied().unwrap_or(self.default_layout)
+    }
+
+    fn key(&mut self, keycode: u32, down: bool) -> Result<(), i32> {
+        if keycode > MAX_KEYCODE {
+            return Err(STATUS_EINVAL);
+        }
+        let translation = self.keyboard.key(self.layout_of(self.focus), keycode, down);
+        self.deliver(&translation);
+        Ok(())
+    }
+
+    fn deliver(&mut self, translation: &Translation) {
+        let flags = self.subscriptions.get(&(self.focus, self.focus_owner)).copied().unwrap_or(0);
+        if flags & SUBSCRIBE_RAW != 0 {
+            self.ipc_channel.send(self.focus_owner, &key_event(self.focus, translation));
+        }
+        if flags & SUBSCRIBE_TEXT != 0 && !translation.text.is_empty() {
+            self.ipc_channel.send(self.focus_owner, &text_event(self.focus, translation.text.as_bytes()));
+        }
+
+        // Windows keep receiving keysyms through the display server
+        if translation.keysym != 0 && !matches!(self.focus, Target::Vt(_)) {
+            let mut request = Vec::with_capacity(12);
+            for value in [DISPLAY_OP_INPUT_KEY, translation.keysym, translation.down as u32] {
+                request.extend_from_slice(&value.to_le_bytes());
+            }
+            let _ = self.display.post(&request);
+        }
+    }
+
+    fn focus(&mut self, target: Target, owner: u64) {
+        if target != self.focus {
+            self.keyboard.cancel_dead_key();
+        }
+        self.focus = target;
+        self.focus_owner = if target == Target::Default { 0 } else { owner };
+    }
+
+    fn forget(&mut self, target: Target) -> Result<(), i32> {
+        if target == Target::Default {
+            return Err(STATUS_EINVAL);
+        }
+        self.layouts.remove(&target);
+        self.subscriptions.retain(|&(subscribed, _), _| subscribed != target);
+        if self.focus == target {
+            self.focus(Target::Default, 0);
+        }
+        Ok(())
+    }
+
+    fn set_layout(&mut self, target: Target, id: u32) -> Result<(), i32> {
+        let layout = Layout::from_id(id).ok_or(STATUS_ENOENT)?;
+        if target == Target::Default {
+            self.default_layout = layout;
+        } else if self.layouts.len() >= MAX_LAYOUTS && !self.layouts.contains_key(&target) {
+            return Err(STATUS_ENOSPC);
+        } else {
+            self.layouts.insert(target, layout);
+        }
+        // A pending accent belongs to the old layout
+        if target == self.focus || target == Target::Default {
+            self.keyboard.cancel_dead_key();
+        }
+        Ok(())
+    }
+
+    fn subscribe(&mut self, sender: u64, target: Target, flags: u32) -> Result<(), i32> {
+        if target == Target::Default || flags & !(SUBSCRIBE_RAW | SUBSCRIBE_TEXT) != 0 {
+            return Err(STATUS_EINVAL);
+        }
+        if flags == 0 {
+            self.subscriptions.remove(&(target, sender));
+            return Ok(());
+        }
+        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS && !self.subscriptions.contains_key(&(target, sender)) {
+            return Err(STATUS_ENOSPC);
+        }
+        self.subscriptions.insert((target, sender), flags);
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match KeymapRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            KeymapRequest::Key { .. } | KeymapRequest::Focus { .. } | KeymapRequest::Forget { .. } => CAP_ADMIN,
+            KeymapRequest::SetLayout { .. } => CAP_WRITE,
+            _ => CAP_READ,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let sender = message.sender;
+        let mut payload = Vec::new();
+        let result = match request {
+            KeymapRequest::Key { keycode, down } => self.key(keycode, down),
+            KeymapRequest::Focus { target, owner } => {
+                self.focus(target, owner);
+                Ok(())
+            }
+            KeymapRequest::Forget { target } => self.forget(target),
+            KeymapRequest::SetLayout { target, layout } => self.set_layout(target, layout),
+            KeymapRequest::GetLayout { target } => {
+                payload.extend_from_slice(&self.layout_of(target).id().to_le_bytes());
+                Ok(())
+            }
+            KeymapRequest::ListLayouts => {
+                for layout in LAYOUTS {
+                    let mut name = [0u8; 8];
+                    name[..layout.name().len()].copy_from_slice(layout.name().as_bytes());
+                    payload.extend_from_slice(&layout.id().to_le_bytes());
+                    payload.extend_from_slice(&name);
+                }
+                Ok(())
+            }
+            KeymapRequest::Subscribe { target, flags } => self.subscribe(sender, target, flags),
+            KeymapRequest::Unsubscribe { target } => self.subscribe(sender, target, 0),
+        };
+
+        let status = match result {
+            Ok(()) => STATUS_OK,
+            Err(status) => status,
+        };
+        self.ipc_channel.send(sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    let mut server = KeymapServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * Orion Operating System - Keymap Server Protocol
+ *
+ * IPC requests of the keymap server. All fields are little-endian; every
+ * message starts with a 32-bit opcode and every reply starts with a 32-bit
+ * signed status (0 or a negative errno).
+ *
+ *   KEY           keycode:u32 down:u32             -> (empty)
+ *   FOCUS         target owner:u64                 -> (empty)
+ *   FORGET        target                           -> (empty)
+ *   SET_LAYOUT    target layout:u32                -> (empty)
+ *   GET_LAYOUT    target                           -> layout:u32
+ *   LIST_LAYOUTS  (none)                           -> records: layout:u32
+ *                                                     name:[u8; 8]
+ *   SUBSCRIBE     target flags:u32                 -> (empty)
+ *   UNSUBSCRIBE   target                           -> (empty)
+ *
+ * A target is kind:u32 (0 default, 1 virtual terminal, 2 display window)
+ * followed by id:u32. Input drivers report keys as USB HID usages (see
+ * layout.rs); the display server reports the focused window and the
+ * endpoint owning it with FOCUS and destroyed windows with FORGET, and the
+ * console does the same for the active virtual terminal. FOCUS on the
+ * default target means nothing has the focus. SET_LAYOUT on the default
+ * target changes the layout of every target without one of its own.
+ *
+ * Events only go to the owner of the focused target, so subscribing to
+ * another process's window yields nothing. For each key it receives KEY
+ * (target keycode:u32 keysym:u32 modifiers:u32 down:u32) with
+ * SUBSCRIBE_RAW, and TEXT (target length:u32 utf8) for presses that type
+ * something with SUBSCRIBE_TEXT. Keysyms are also forwarded to the display
+ * server while a window (or nothing) has the focus.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use crate::compose::Translation;
+
+// Opcodes
+pub const OP_KEY: u32 = 1;
+pub const OP_FOCUS: u32 = 2;
+pub const OP_FORGET: u32 = 3;
+pub const OP_SET_LAYOUT: u32 = 4;
+pub const OP_GET_LAYOUT: u32 = 5;
+pub const OP_LIST_LAYOUTS: u32 = 6;
+pub const OP_SUBSCRIBE: u32 = 7;
+pub const OP_UNSUBSCRIBE: u32 = 8;
+
+// Events sent to subscribers
+pub const EVENT_KEY: u32 = 0x8001;
+pub const EVENT_TEXT: u32 = 0x8002;
+
+// Target kinds
+pub const TARGET_DEFAULT: u32 = 0;
+pub const TARGET_VT: u32 = 1;
+pub const TARGET_WINDOW: u32 = 2;
+
+// Subscription flags
+pub const SUBSCRIBE_RAW: u32 = 1 << 0;
+pub const SUBSCRIBE_TEXT: u32 = 1 << 1;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Target {
+    Default,
+    Vt(u32),
+    Window(u32),
+}
+
+impl Target {
+    fn encode(self, out: &mut Vec<u8>) {
+        let (kind, id) = match self {
+            Target::Default => (TARGET_DEFAULT, 0),
+            Target::Vt(id) => (TARGET_VT, id),
+            Target::Window(id) => (TARGET_WINDOW, id),
+        };
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&id.to_le_bytes());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeymapRequest {
+    Key { keycode: u32, down: bool },
+    Focus { target: Target, owner: u64 },
+    Forget { target: Target },
+    SetLayout { target: Target, layout: u32 },
+    GetLayout { target: Target },
+    ListLayouts,
+    Subscribe { target: Target, flags: u32 },
+    Unsubscribe { target: Target },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+fn read_target(data: &[u8], offset: usize) -> Option<Target> {
+    let id = read_u32(data, offset + 4)?;
+    match read_u32(data, offset)? {
+        TARGET_DEFAULT => Some(Target::Default),
+        TARGET_VT => Some(Target::Vt(id)),
+        TARGET_WINDOW => Some(Target::Window(id)),
+        _ => None,
+    }
+}
+
+impl KeymapRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_KEY => Some(KeymapRequest::Key { keycode: read_u32(data, 4)?, down: read_u32(data, 8)? != 0 }),
+            OP_FOCUS => Some(KeymapRequest::Focus { target: read_target(data, 4)?, owner: read_u64(data, 12)? }),
+            OP_FORGET => Some(KeymapRequest::Forget { target: read_target(data, 4)? }),
+            OP_SET_LAYOUT => {
+                Some(KeymapRequest::SetLayout { target: read_target(data, 4)?, layout: read_u32(data, 12)? })
+            }
+            OP_GET_LAYOUT => Some(KeymapRequest::GetLayout { target: read_target(data, 4)? }),
+            OP_LIST_LAYOUTS => Some(KeymapRequest::ListLayouts),
+            OP_SUBSCRIBE => {
+                Some(KeymapRequest::Subscribe { target: read_target(data, 4)?, flags: read_u32(data, 12)? })
+            }
+            OP_UNSUBSCRIBE => Some(KeymapRequest::Unsubscribe { target: read_target(data, 4)? }),
+            _ => None,
+        }
+    }
+}
+
+/// KEY event for a raw subscriber
+pub fn key_event(target: Target, translation: &Translation) -> Vec<u8> {
+    let mut out = Vec::with_capacity(28);
+    out.extend_from_slice(&EVENT_KEY.to_le_bytes());
+    target.encode(&mut out);
+    for value in [translation.keycode, translation.keysym, translation.modifiers, translation.down as u32] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out
+}
+
+/// TEXT event for a text subscriber
+pub fn text_event(target: Target, text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + text.len());
+    out.extend_from_slice(&EVENT_TEXT.to_le_bytes());
+    target.encode(&mut out);
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    out.extend_from_slice(text);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut message = Vec::new();
+        for value in [OP_SET_LAYOUT, TARGET_WINDOW, 7, 3] {
+            message.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(
+            KeymapRequest::decode(&message),
+            Some(KeymapRequest::SetLayout { target: Target::Window(7), layout: 3 })
+        );
+        assert_eq!(KeymapRequest::decode(&message[..12]), None);
+        message[4..8].copy_from_slice(&9u32.to_le_bytes());
+        assert_eq!(KeymapRequest::decode(&message), None);
+
+        let mut event = Vec::new();
+        Target::Vt(2).encode(&mut event);
+        assert_eq!(&text_event(Target::Vt(2), "é".as_bytes())[4..12], &event[..]);
+        assert_eq!(text_event(Target::Vt(2), "é".as_bytes()).len(), 18);
+    }
+}
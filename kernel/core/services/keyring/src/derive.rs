@@ -0,0 +1,71 @@
+/*
+ * Orion Operating System - Keyring Key Derivation
+ *
+ * Symmetric keys stay in the keyring like private keys do; a service
+ * holding a USE grant gets keys derived from one for a purpose it names
+ * instead. The fs server derives the key of an encrypted directory from
+ * the key a session adds for its user, and two services never get the
+ * same key unless they name the same purpose.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use orion_crypto::kdf::hkdf_sha512;
+
+use crate::keystore::KeyType;
+
+/// Length of a derived key
+pub const DERIVED_KEY_SIZE: usize = 64;
+
+/// Shortest key material accepted, 256 bits
+const MIN_SECRET_SIZE: usize = 32;
+
+/// Salt binding every derived key to the keyring
+const DERIVE_SALT: &[u8] = b"orion-keyring-derive-v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeriveError {
+    /// Only symmetric keys are derived from
+    WrongType,
+    /// The key is too short to derive from
+    WeakKey,
+    /// No purpose named
+    NoPurpose,
+}
+
+/// Key derived from the symmetric key in `data` for `purpose`
+pub fn derive(key_type: KeyType, data: &[u8], purpose: &[u8]) -> Result<[u8; DERIVED_KEY_SIZE], DeriveError> {
+    if key_type != KeyType::Symmetric {
+        return Err(DeriveError::WrongType);
+    }
+    if data.len() < MIN_SECRET_SIZE {
+        return Err(DeriveError::WeakKey);
+    }
+    if purpose.is_empty() {
+        return Err(DeriveError::NoPurpose);
+    }
+    let mut out = [0u8; DERIVED_KEY_SIZE];
+    hkdf_sha512(DERIVE_SALT, data, purpose, &mut out);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derives_per_purpose() {
+        let key = [0x33u8; 32];
+        let fs = derive(KeyType::Symmetric, &key, b"fscrypt").unwrap();
+        assert_eq!(derive(KeyType::Symmetric, &key, b"fscrypt"), Ok(fs));
+        assert_ne!(derive(KeyType::Symmetric, &key, b"backup").unwrap(), fs);
+
+        assert_eq!(derive(KeyType::TlsPrivateKey, &key, b"fscrypt"), Err(DeriveError::WrongType));
+        assert_eq!(derive(KeyType::Symmetric, &key[..16], b"fscrypt"), Err(DeriveError::WeakKey));
+        assert_eq!(derive(KeyType::Symmetric, &key, b""), Err(DeriveError::NoPurpose));
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * Orion Operating System - Key Store
+ *
+ * Key material storage for the keyring service. Payloads live in a single
+ * arena that the server locks in memory at startup; only metadata is kept
+ * on the regular heap. Consumers refer to keys through opaque handles and
+ * every access is checked against the owner or an explicit grant.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::wipe;
+
+// ========================================
+// KEY STORE CONSTANTS
+// ========================================
+
+/// Size of one payload slot; large enough for an RSA-4096 private key in DER
+pub const KEY_SLOT_SIZE: usize = 4096;
+
+/// Number of payload slots in the locked arena
+pub const KEY_SLOT_COUNT: usize = 64;
+
+/// Maximum length of a key description
+pub const MAX_DESCRIPTION_LEN: usize = 128;
+
+/// Maximum number of processes a single key can be granted to
+const MAX_GRANTS_PER_KEY: usize = 16;
+
+// Permission bits carried by owners and grants
+pub const KEY_PERM_VIEW: u32 = 0x01;
+pub const KEY_PERM_READ: u32 = 0x02;
+pub const KEY_PERM_USE: u32 = 0x04;
+pub const KEY_PERM_GRANT: u32 = 0x08;
+pub const KEY_PERM_REVOKE: u32 = 0x10;
+pub const KEY_PERM_ALL: u32 = 0x1F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    StoragePoolKey = 0,
+    TlsPrivateKey = 1,
+    TlsCertificate = 2,
+    NbdCredential = 3,
+    Symmetric = 4,
+    Generic = 5,
+}
+
+impl KeyType {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(KeyType::StoragePoolKey),
+            1 => Some(KeyType::TlsPrivateKey),
+            2 => Some(KeyType::TlsCertificate),
+            3 => Some(KeyType::NbdCredential),
+            4 => Some(KeyType::Symmetric),
+            5 => Some(KeyType::Generic),
+            _ => None,
+        }
+    }
+
+    /// Whether the payload may ever leave the keyring through READ.
+    ///
+    /// Private keys and storage keys are only usable through the services
+    /// that hold a USE grant; certificates and generic blobs are public enough
+    /// to be read back by their owner.
+    pub fn is_readable(self) -> bool {
+        matches!(self, KeyType::TlsCertificate | KeyType::NbdCredential | KeyType::Generic)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyError {
+    NotFound,
+    PermissionDenied,
+    NoSpace,
+    TooLarge,
+    InvalidArgument,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KeyGrant {
+    pid: u64,
+    perms: u32,
+}
+
+/// Public metadata of a key, returned by DESCRIBE
+#[derive(Debug, Clone)]
+pub struct KeyInfo {
+    pub key_type: KeyType,
+    pub owner: u64,
+    pub perms: u32,
+    pub length: u32,
+    pub sealed: bool,
+    pub description: String,
+}
+
+struct KeyEntry {
+    slot: usize,
+    length: usize,
+    key_type: KeyType,
+    owner: u64,
+    description: String,
+    grants: Vec<KeyGrant>,
+    sealed: bool,
+}
+
+// ========================================
+// KEY STORE
+// ========================================
+
+pub struct KeyStore<'a> {
+    arena: &'a mut [u8],
+    slot_used: [bool; KEY_SLOT_COUNT],
+    keys: BTreeMap<u64, KeyEntry>,
+}
+
+impl<'a> KeyStore<'a> {
+    /// Build a store over `arena`, which must hold KEY_SLOT_COUNT slots
+    pub fn new(arena: &'a mut [u8]) -> Self {
+        assert!(arena.len() >= KEY_SLOT_SIZE * KEY_SLOT_COUNT);
+        Self {
+            arena,
+            slot_used: [false; KEY_SLOT_COUNT],
+            keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn contains(&self, handle: u64) -> bool {
+        self.keys.contains_key(&handle)
+    }
+
+    /// Store `data` under `handle` on behalf of `owner`
+    pub fn add(
+        &mut self,
+        handle: u64,
+        owner: u64,
+        key_type: KeyType,
+        description: &str,
+        data: &[u8],
+    ) -> Result<(), KeyError> {
+        if handle == 0 || self.keys.contains_key(&handle) || description.len() > MAX_DESCRIPTION_LEN {
+            return Err(KeyError::InvalidArgument);
+        }
+        if data.is_empty() || data.len() > KEY_SLOT_SIZE {
+            return Err(KeyError::TooLarge);
+        }
+
+        let slot = self.slot_used.iter().position(|used| !used).ok_or(KeyError::NoSpace)?;
+        self.slot_used[slot] = true;
+        self.slot_mut(slot)[..data.len()].copy_from_slice(data);
+
+        self.keys.insert(
+            handle,
+            KeyEntry {
+                slot,
+                length: data.len(),
+                key_type,
+                owner,
+                description: String::from(description),
+                grants: Vec::new(),
+                sealed: false,
+            },
+        );
+        Ok(())
+    }
+
+    /// Permissions `pid` holds on `handle`
+    pub fn permissions(&self, handle: u64, pid: u64) -> Result<u32, KeyError> {
+        let entry = self.keys.get(&handle).ok_or(KeyError::NotFound)?;
+        if entry.owner == pid {
+            return Ok(KEY_PERM_ALL);
+        }
+        Ok(entry
+            .grants
+            .iter()
+            .find(|grant| grant.pid == pid)
+            .map(|grant| grant.perms)
+            .unwrap_or(0))
+    }
+
+    fn check(&self, handle: u64, pid: u64, required: u32) -> Result<&KeyEntry, KeyError> {
+        let perms = self.permissions(handle, pid)?;
+        if perms & required != required {
+            // Do not reveal whether the handle exists to callers without VIEW
+            return Err(if perms & KEY_PERM_VIEW != 0 {
+                KeyError::PermissionDenied
+            } else {
+                KeyError::NotFound
+            });
+        }
+        Ok(&self.keys[&handle])
+    }
+
+    pub fn describe(&self, handle: u64, pid: u64) -> Result<KeyInfo, KeyError> {
+        let perms = self.permissions(handle, pid)?;
+        let entry = self.check(handle, pid, KEY_PERM_VIEW)?;
+        Ok(KeyInfo {
+            key_type: entry.key_type,
+            owner: entry.owner,
+            perms,
+            length: entry.length as u32,
+            sealed: entry.sealed,
+            description: entry.description.clone(),
+        })
+    }
+
+    /// Copy the payload out for a caller allowed to READ a readable key
+    pub fn read(&self, handle: u64, pid: u64, out: &mut Vec<u8>) -> Result<(), KeyError> {
+        let entry = self.check(handle, pid, KEY_PERM_READ)?;
+        if !entry.key_type.is_readable() {
+            return Err(KeyError::PermissionDenied);
+        }
+        out.extend_from_slice(&self.slot(entry.slot)[..entry.length]);
+        Ok(())
+    }
+
+    /// Borrow the payload for an operation performed inside the keyring on
+    /// behalf of a caller holding USE, such as sealing
+    pub fn with_payload<R>(
+        &self,
+        handle: u64,
+        pid: u64,
+        f: impl FnOnce(KeyType, &[u8]) -> R,
+    ) -> Result<R, KeyError> {
+        let entry = self.check(handle, pid, KEY_PERM_USE)?;
+        Ok(f(entry.key_type, &self.slot(entry.slot)[..entry.length]))
+    }
+
+    /// Give `grantee` a subset of the permissions `pid` holds
+    pub fn grant(&mut self, handle: u64, pid: u64, grantee: u64, perms: u32) -> Result<(), KeyError> {
+        let held = self.permissions(handle, pid)?;
+        self.check(handle, pid, KEY_PERM_GRANT)?;
+        if perms & !held != 0 {
+            return Err(KeyError::PermissionDenied);
+        }
+
+        let entry = self.keys.get_mut(&handle).ok_or(KeyError::NotFound)?;
+        if grantee == entry.owner {
+            return Ok(());
+        }
+        if let Some(grant) = entry.grants.iter_mut().find(|grant| grant.pid == grantee) {
+            grant.perms = perms;
+        } else if perms != 0 {
+            if entry.grants.len() >= MAX_GRANTS_PER_KEY {
+                return Err(KeyError::NoSpace);
+            }
+            entry.grants.push(KeyGrant { pid: grantee, perms });
+        }
+        entry.grants.retain(|grant| grant.perms != 0);
+        Ok(())
+    }
+
+    pub fn set_sealed(&mut self, handle: u64, sealed: bool) {
+        if let Some(entry) = self.keys.get_mut(&handle) {
+            entry.sealed = sealed;
+        }
+    }
+
+    /// Destroy a key and wipe its slot
+    pub fn revoke(&mut self, handle: u64, pid: u64) -> Result<(), KeyError> {
+        self.check(handle, pid, KEY_PERM_REVOKE)?;
+        let entry = self.keys.remove(&handle).ok_or(KeyError::NotFound)?;
+        wipe(self.slot_mut(entry.slot));
+        self.slot_used[entry.slot] = false;
+        Ok(())
+    }
+
+    /// Drop every key owned by an exited process and any grant it held
+    pub fn release_process(&mut self, pid: u64) -> usize {
+        let owned: Vec<u64> = self
+            .keys
+            .iter()
+            .filter(|(_, entry)| entry.owner == pid)
+            .map(|(handle, _)| *handle)
+            .collect();
+
+        for handle in owned.iter() {
+            let _ = self.revoke(*handle, pid);
+        }
+        for entry in self.keys.values_mut() {
+            entry.grants.retain(|grant| grant.pid != pid);
+        }
+        owned.len()
+    }
+
+    fn slot(&self, slot: usize) -> &[u8] {
+        &self.arena[slot * KEY_SLOT_SIZE..(slot + 1) * KEY_SLOT_SIZE]
+    }
+
+    fn slot_mut(&mut self, slot: usize) -> &mut [u8] {
+        &mut self.arena[slot * KEY_SLOT_SIZE..(slot + 1) * KEY_SLOT_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_grant_limits_access() {
+        let mut arena = vec![0u8; KEY_SLOT_SIZE * KEY_SLOT_COUNT];
+        let mut store = KeyStore::new(&mut arena);
+        store.add(0x1234, 10, KeyType::TlsPrivateKey, "web", &[0x42; 32]).unwrap();
+
+        // Strangers cannot even tell the key exists
+        assert_eq!(store.describe(0x1234, 20).unwrap_err(), KeyError::NotFound);
+
+        store.grant(0x1234, 10, 20, KEY_PERM_VIEW | KEY_PERM_USE).unwrap();
+        assert!(store.describe(0x1234, 20).is_ok());
+        assert_eq!(store.with_payload(0x1234, 20, |_, data| data.len()), Ok(32));
+
+        // Private keys are never readable, not even by their owner
+        let mut out = Vec::new();
+        assert_eq!(store.read(0x1234, 10, &mut out), Err(KeyError::PermissionDenied));
+
+        // A grantee cannot escalate through GRANT
+        assert_eq!(store.grant(0x1234, 20, 30, KEY_PERM_USE), Err(KeyError::PermissionDenied));
+    }
+
+    #[test]
+    fn test_revoke_wipes_slot() {
+        let mut arena = vec![0u8; KEY_SLOT_SIZE * KEY_SLOT_COUNT];
+        {
+            let mut store = KeyStore::new(&mut arena);
+            store.add(7, 1, KeyType::Generic, "blob", &[0xAA; 64]).unwrap();
+            let mut out = Vec::new();
+            store.read(7, 1, &mut out).unwrap();
+            assert_eq!(out, vec![0xAA; 64]);
+
+            store.revoke(7, 1).unwrap();
+            assert!(!store.contains(7));
+        }
+        assert!(arena.iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,290 @@
+/*
+ * Orion Operating System - Keyring Server
+ *
+ * Holds key material for the rest of the system: storage pool keys, TLS
+ * private keys and certificates, NBD credentials. Payloads are kept in a
+ * locked arena that is never swapped nor dumped, callers only ever receive
+ * opaque handles, and each handle carries per-process permissions. When a
+ * TPM is available keys can be sealed to the platform for persistent
+ * storage. Private keys can be used in place: SIGN produces a signature
+ * for a holder of a USE grant without the key ever being copied out.
+ * VERIFY checks a signature against a public key passed in the request,
+ * so that C servers (the net server's TLS client) share the Ed25519 code.
+ * Symmetric keys are used the same way: DERIVE gives a holder of a USE
+ * grant a key derived from one for the purpose it names.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_cap::Capability;
+use orion_crypto::{ed25519, wipe};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_CREATE, CAP_READ};
+use orion_sys::{madvise, MADV_LOCK};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod derive;
+mod keystore;
+mod protocol;
+mod seal;
+mod sign;
+
+use keystore::{KeyStore, KeyType, KEY_SLOT_COUNT, KEY_SLOT_SIZE};
+use protocol::*;
+use seal::{NoSealBackend, SealBackend, TpmSealBackend};
+
+/// Only the kernel may report process exits
+const KERNEL_ENDPOINT: u64 = 0;
+
+/// Opcode and length of the entropy service GET_RANDOM request
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+
+/// Handle generation gives up after this many collisions
+const HANDLE_ATTEMPTS: usize = 4;
+
+#[repr(C, align(4096))]
+struct KeyArena([u8; KEY_SLOT_SIZE * KEY_SLOT_COUNT]);
+
+/// Backing memory for every key payload, locked at startup
+static mut KEY_ARENA: KeyArena = KeyArena([0; KEY_SLOT_SIZE * KEY_SLOT_COUNT]);
+
+struct KeyringServer {
+    store: KeyStore<'static>,
+    sealer: Box<dyn SealBackend>,
+    entropy: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl KeyringServer {
+    fn new() -> Self {
+        let arena = unsafe { &mut (*core::ptr::addr_of_mut!(KEY_ARENA)).0 };
+
+        // Pin the arena before any key lands in it
+        if madvise(arena.as_ptr() as u64, arena.len(), MADV_LOCK).is_err() {
+            panic!("keyring: unable to lock key arena");
+        }
+
+        let sealer: Box<dyn SealBackend> = match TpmSealBackend::detect() {
+            Some(tpm) => Box::new(tpm),
+            None => Box::new(NoSealBackend),
+        };
+
+        Self {
+            store: KeyStore::new(arena),
+            sealer,
+            entropy: IpcChannel::connect("entropy"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            match self.ipc_channel.receive() {
+                Some(message) => self.handle_message(message),
+                None => self.ipc_channel.wait(),
+            }
+        }
+    }
+
+    fn handle_message(&mut self, mut message: IpcMessage) {
+        let request = KeyringRequest::decode(&message.data);
+        // Requests may carry key material; do not leave it in the heap
+        wipe(&mut message.data);
+
+        let request = match request {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        if let KeyringRequest::ProcessExit { pid } = request {
+            if message.sender == KERNEL_ENDPOINT {
+                self.store.release_process(pid);
+            }
+            return;
+        }
+
+        let required = match request {
+            KeyringRequest::AddKey { .. } | KeyringRequest::Unseal { .. } => CAP_CREATE,
+            _ => CAP_READ,
+        };
+        if !self.capabilities.check_rights(message.capability, required, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let sender = message.sender;
+        let mut payload = Vec::new();
+        let status = match request {
+            KeyringRequest::AddKey { key_type, description, mut data } => {
+                let status = self.handle_add(sender, key_type, &description, &data, &mut payload);
+                wipe(&mut data);
+                status
+            }
+            KeyringRequest::Describe { handle } => match self.store.describe(handle, sender) {
+                Ok(info) => {
+                    encode_key_info(&info, &mut payload);
+                    STATUS_OK
+                }
+                Err(error) => key_error_status(error),
+            },
+            KeyringRequest::Read { handle } => match self.store.read(handle, sender, &mut payload) {
+                Ok(()) => STATUS_OK,
+                Err(error) => key_error_status(error),
+            },
+            KeyringRequest::Grant { handle, grantee, perms } => {
+                match self.store.grant(handle, sender, grantee, perms) {
+                    Ok(()) => STATUS_OK,
+                    Err(error) => key_error_status(error),
+                }
+            }
+            KeyringRequest::Revoke { handle } => match self.store.revoke(handle, sender) {
+                Ok(()) => STATUS_OK,
+                Err(error) => key_error_status(error),
+            },
+            KeyringRequest::Seal { handle } => self.handle_seal(sender, handle, &mut payload),
+            KeyringRequest::Unseal { key_type, description, blob } => {
+                self.handle_unseal(sender, key_type, &description, &blob, &mut payload)
+            }
+            KeyringRequest::Sign { handle, message } => {
+                match self.store.with_payload(handle, sender, |key_type, data| sign::sign(key_type, data, &message)) {
+                    Ok(Ok(signature)) => {
+                        payload.extend_from_slice(&signature);
+                        STATUS_OK
+                    }
+                    Ok(Err(error)) => sign_error_status(error),
+                    Err(error) => key_error_status(error),
+                }
+            }
+            KeyringRequest::Derive { handle, purpose } => {
+                let derived = self.store.with_payload(handle, sender, |key_type, data| {
+                    derive::derive(key_type, data, &purpose)
+                });
+                match derived {
+                    Ok(Ok(mut key)) => {
+                        payload.extend_from_slice(&key);
+                        wipe(&mut key);
+                        STATUS_OK
+                    }
+                    Ok(Err(error)) => derive_error_status(error),
+                    Err(error) => key_error_status(error),
+                }
+            }
+            KeyringRequest::Verify { public_key, signature, message } => {
+                if ed25519::verify(&public_key, &message, &signature) {
+                    STATUS_OK
+                } else {
+                    STATUS_EBADMSG
+                }
+            }
+            KeyringRequest::ProcessExit { .. } => unreachable!(),
+        };
+
+        self.ipc_channel.send(sender, &reply(status, &payload));
+        wipe(&mut payload);
+    }
+
+    fn handle_add(&mut self, sender: u64, key_type: u32, description: &str, data: &[u8], out: &mut Vec<u8>) -> i32 {
+        let key_type = match KeyType::from_u32(key_type) {
+            Some(key_type) => key_type,
+            None => return STATUS_EINVAL,
+        };
+        let handle = match self.new_handle() {
+            Some(handle) => handle,
+            None => return STATUS_EAGAIN,
+        };
+
+        match self.store.add(handle, sender, key_type, description, data) {
+            Ok(()) => {
+                out.extend_from_slice(&handle.to_le_bytes());
+                STATUS_OK
+            }
+            Err(error) => key_error_status(error),
+        }
+    }
+
+    fn handle_seal(&mut self, sender: u64, handle: u64, out: &mut Vec<u8>) -> i32 {
+        let sealer = &mut self.sealer;
+        match self.store.with_payload(handle, sender, |_, data| sealer.seal(data)) {
+            Ok(Ok(blob)) => {
+                out.extend_from_slice(&blob);
+                self.store.set_sealed(handle, true);
+                STATUS_OK
+            }
+            Ok(Err(error)) => seal_error_status(error),
+            Err(error) => key_error_status(error),
+        }
+    }
+
+    fn handle_unseal(&mut self, sender: u64, key_type: u32, description: &str, blob: &[u8], out: &mut Vec<u8>) -> i32 {
+        let mut data = match self.sealer.unseal(blob) {
+            Ok(data) => data,
+            Err(error) => return seal_error_status(error),
+        };
+
+        let status = self.handle_add(sender, key_type, description, &data, out);
+        wipe(&mut data);
+
+        if status == STATUS_OK {
+            let handle = u64::from_le_bytes(out[..8].try_into().unwrap());
+            self.store.set_sealed(handle, true);
+        }
+        status
+    }
+
+    /// Draw an unguessable handle from the entropy service
+    fn new_handle(&mut self) -> Option<u64> {
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+        request.extend_from_slice(&8u32.to_le_bytes());
+        request.extend_from_slice(&0u32.to_le_bytes());
+
+        for _ in 0..HANDLE_ATTEMPTS {
+            let response = self.entropy.call(&request).ok()?;
+            if response.len() < 12 || response[..4] != STATUS_OK.to_le_bytes() {
+                return None;
+            }
+            let handle = u64::from_le_bytes(response[4..12].try_into().unwrap());
+            if handle != 0 && !self.store.contains(handle) {
+                return Some(handle);
+            }
+        }
+        None
+    }
+}
+
+fn main() {
+    let mut server = KeyringServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * Orion Operating System - Keyring Service Protocol
+ *
+ * Wire format of the requests understood by the keyring service. All fields
+ * are little-endian; every message starts with a 32-bit opcode and every
+ * reply starts with a 32-bit signed status (0 or a negative errno). Keys
+ * are always named by the 64-bit handle returned from ADD or UNSEAL.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::derive::DeriveError;
+use crate::keystore::{KeyError, KeyInfo};
+use crate::seal::SealError;
+use crate::sign::SignError;
+
+// Opcodes
+pub const OP_ADD_KEY: u32 = 1;
+pub const OP_DESCRIBE: u32 = 2;
+pub const OP_READ: u32 = 3;
+pub const OP_GRANT: u32 = 4;
+pub const OP_REVOKE: u32 = 5;
+pub const OP_SEAL: u32 = 6;
+pub const OP_UNSEAL: u32 = 7;
+pub const OP_PROCESS_EXIT: u32 = 8;
+pub const OP_SIGN: u32 = 9;
+pub const OP_VERIFY: u32 = 10;
+pub const OP_DERIVE: u32 = 11;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EACCES: i32 = -13;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+pub const STATUS_EBADMSG: i32 = -74;
+pub const STATUS_ENOTSUP: i32 = -95;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyringRequest {
+    AddKey { key_type: u32, description: String, data: Vec<u8> },
+    Describe { handle: u64 },
+    Read { handle: u64 },
+    Grant { handle: u64, grantee: u64, perms: u32 },
+    Revoke { handle: u64 },
+    Seal { handle: u64 },
+    Unseal { key_type: u32, description: String, blob: Vec<u8> },
+    ProcessExit { pid: u64 },
+    Sign { handle: u64, message: Vec<u8> },
+    /// Check an Ed25519 signature made with a key the caller got elsewhere
+    Verify { public_key: [u8; 32], signature: [u8; 64], message: Vec<u8> },
+    /// Derive a 64-byte key from a symmetric key for `purpose`
+    Derive { handle: u64, purpose: Vec<u8> },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode `key_type, description_len, description, payload`
+fn read_named_payload(data: &[u8]) -> Option<(u32, String, Vec<u8>)> {
+    let key_type = read_u32(data, 4)?;
+    let description_len = read_u32(data, 8)? as usize;
+    let description = data.get(12..12 + description_len)?;
+    let description = String::from(core::str::from_utf8(description).ok()?);
+    let payload = data.get(12 + description_len..)?.to_vec();
+    Some((key_type, description, payload))
+}
+
+impl KeyringRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_ADD_KEY => {
+                let (key_type, description, data) = read_named_payload(data)?;
+                Some(KeyringRequest::AddKey { key_type, description, data })
+            }
+            OP_DESCRIBE => Some(KeyringRequest::Describe { handle: read_u64(data, 4)? }),
+            OP_READ => Some(KeyringRequest::Read { handle: read_u64(data, 4)? }),
+            OP_GRANT => Some(KeyringRequest::Grant {
+                handle: read_u64(data, 4)?,
+                grantee: read_u64(data, 12)?,
+                perms: read_u32(data, 20)?,
+            }),
+            OP_REVOKE => Some(KeyringRequest::Revoke { handle: read_u64(data, 4)? }),
+            OP_SEAL => Some(KeyringRequest::Seal { handle: read_u64(data, 4)? }),
+            OP_UNSEAL => {
+                let (key_type, description, blob) = read_named_payload(data)?;
+                Some(KeyringRequest::Unseal { key_type, description, blob })
+            }
+            OP_PROCESS_EXIT => Some(KeyringRequest::ProcessExit { pid: read_u64(data, 4)? }),
+            OP_SIGN => Some(KeyringRequest::Sign {
+                handle: read_u64(data, 4)?,
+                message: data.get(12..)?.to_vec(),
+            }),
+            OP_VERIFY => Some(KeyringRequest::Verify {
+                public_key: data.get(4..36)?.try_into().ok()?,
+                signature: data.get(36..100)?.try_into().ok()?,
+                message: data.get(100..)?.to_vec(),
+            }),
+            OP_DERIVE => Some(KeyringRequest::Derive {
+                handle: read_u64(data, 4)?,
+                purpose: data.get(12..)?.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub fn key_error_status(error: KeyError) -> i32 {
+    match error {
+        KeyError::NotFound => STATUS_ENOENT,
+        KeyError::PermissionDenied => STATUS_EACCES,
+        KeyError::NoSpace => STATUS_ENOSPC,
+        KeyError::TooLarge => STATUS_EINVAL,
+        KeyError::InvalidArgument => STATUS_EINVAL,
+    }
+}
+
+pub fn seal_error_status(error: SealError) -> i32 {
+    match error {
+        SealError::Unsupported => STATUS_ENOTSUP,
+        SealError::DeviceError => STATUS_EIO,
+        SealError::InvalidBlob => STATUS_EINVAL,
+        SealError::TooLarge => STATUS_EINVAL,
+    }
+}
+
+pub fn sign_error_status(error: SignError) -> i32 {
+    match error {
+        SignError::WrongType => STATUS_EINVAL,
+        SignError::UnsupportedKey => STATUS_ENOTSUP,
+    }
+}
+
+pub fn derive_error_status(error: DeriveError) -> i32 {
+    match error {
+        DeriveError::WrongType => STATUS_EINVAL,
+        DeriveError::WeakKey => STATUS_EINVAL,
+        DeriveError::NoPurpose => STATUS_EINVAL,
+    }
+}
+
+/// DESCRIBE payload: type, perms, length, sealed flag, owner, then the description
+pub fn encode_key_info(info: &KeyInfo, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(info.key_type as u32).to_le_bytes());
+    out.extend_from_slice(&info.perms.to_le_bytes());
+    out.extend_from_slice(&info.length.to_le_bytes());
+    out.extend_from_slice(&(info.sealed as u32).to_le_bytes());
+    out.extend_from_slice(&info.owner.to_le_bytes());
+    out.extend_from_slice(info.description.as_bytes());
+}
@@ -0,0 +1,110 @@
+/*
+ * Orion Operating System - Key Sealing
+ *
+ * Sealing binds a key to the platform so that the blob handed out for
+ * persistent storage is useless on another machine. The keyring talks to
+ * the hardware through the SealBackend trait; with a TPM present, sealing
+ * goes through the TPM driver, otherwise it is reported as unsupported.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+// TPM driver protocol (see drivers/char/src/tpm.rs)
+const TPM_OP_GET_RANDOM: u32 = 4;
+const TPM_OP_SEAL: u32 = 6;
+const TPM_OP_UNSEAL: u32 = 7;
+
+/// Largest secret the TPM can seal in one data object
+const TPM_MAX_SEALED_DATA: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SealError {
+    Unsupported,
+    DeviceError,
+    InvalidBlob,
+    TooLarge,
+}
+
+/// Hardware able to encrypt data to the current platform state
+pub trait SealBackend {
+    /// Encrypt `data` into a blob only this platform can open
+    fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>, SealError>;
+
+    /// Recover the plaintext of a blob produced by `seal`
+    fn unseal(&mut self, blob: &[u8]) -> Result<Vec<u8>, SealError>;
+}
+
+/// Backend used when no TPM is present
+pub struct NoSealBackend;
+
+impl SealBackend for NoSealBackend {
+    fn seal(&mut self, _data: &[u8]) -> Result<Vec<u8>, SealError> {
+        Err(SealError::Unsupported)
+    }
+
+    fn unseal(&mut self, _blob: &[u8]) -> Result<Vec<u8>, SealError> {
+        Err(SealError::Unsupported)
+    }
+}
+
+/// Seals to the TPM storage root key through the TPM driver
+pub struct TpmSealBackend {
+    channel: IpcChannel,
+}
+
+impl TpmSealBackend {
+    /// Returns a backend only when the TPM driver answers
+    pub fn detect() -> Option<Self> {
+        let mut backend = Self { channel: IpcChannel::connect("tpm") };
+        let mut probe = Vec::with_capacity(8);
+        probe.extend_from_slice(&TPM_OP_GET_RANDOM.to_le_bytes());
+        probe.extend_from_slice(&1u32.to_le_bytes());
+        backend.request(&probe).ok()?;
+        Some(backend)
+    }
+
+    fn request(&mut self, message: &[u8]) -> Result<Vec<u8>, SealError> {
+        let response = self.channel.call(message).map_err(|_| SealError::DeviceError)?;
+        if response.len() < 4 {
+            return Err(SealError::DeviceError);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            0 => Ok(response[4..].to_vec()),
+            -22 => Err(SealError::InvalidBlob),
+            _ => Err(SealError::DeviceError),
+        }
+    }
+
+    fn command(opcode: u32, payload: &[u8]) -> Vec<u8> {
+        let mut message = Vec::with_capacity(4 + payload.len());
+        message.extend_from_slice(&opcode.to_le_bytes());
+        message.extend_from_slice(payload);
+        message
+    }
+}
+
+impl SealBackend for TpmSealBackend {
+    fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>, SealError> {
+        if data.len() > TPM_MAX_SEALED_DATA {
+            return Err(SealError::TooLarge);
+        }
+        let mut message = Self::command(TPM_OP_SEAL, data);
+        let result = self.request(&message);
+        orion_crypto::wipe(&mut message);
+        result
+    }
+
+    fn unseal(&mut self, blob: &[u8]) -> Result<Vec<u8>, SealError> {
+        self.request(&Self::command(TPM_OP_UNSEAL, blob))
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Orion Operating System - Keyring Signing
+ *
+ * Private keys never leave the keyring; services holding a USE grant ask
+ * the keyring to sign on their behalf instead (the net server does so for
+ * the TLS CertificateVerify message). Ed25519 keys are accepted either as
+ * the raw 32-byte seed or as the PKCS#8 DER document produced by common
+ * tooling.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use orion_crypto::ed25519::{self, SECRET_KEY_SIZE, SIGNATURE_SIZE};
+use orion_crypto::wipe;
+
+use crate::keystore::KeyType;
+
+/// PKCS#8 v1 prefix of an Ed25519 private key (RFC 8410), seed follows
+const PKCS8_ED25519_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignError {
+    /// The key is not a private key
+    WrongType,
+    /// The payload is not an Ed25519 key in a supported encoding
+    UnsupportedKey,
+}
+
+fn ed25519_seed(data: &[u8]) -> Option<[u8; SECRET_KEY_SIZE]> {
+    let seed = match data.len() {
+        SECRET_KEY_SIZE => data,
+        48 if data[..16] == PKCS8_ED25519_PREFIX => &data[16..],
+        _ => return None,
+    };
+    let mut out = [0u8; SECRET_KEY_SIZE];
+    out.copy_from_slice(seed);
+    Some(out)
+}
+
+/// Sign `message` with the private key stored in `data`
+pub fn sign(key_type: KeyType, data: &[u8], message: &[u8]) -> Result<[u8; SIGNATURE_SIZE], SignError> {
+    if key_type != KeyType::TlsPrivateKey {
+        return Err(SignError::WrongType);
+    }
+    let mut seed = ed25519_seed(data).ok_or(SignError::UnsupportedKey)?;
+    let signature = ed25519::sign(&seed, message);
+    wipe(&mut seed);
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signs_with_raw_and_pkcs8_keys() {
+        let seed = [0x5au8; 32];
+        let public_key = ed25519::public_key(&seed);
+
+        let raw = sign(KeyType::TlsPrivateKey, &seed, b"hello").unwrap();
+        assert!(ed25519::verify(&public_key, b"hello", &raw));
+
+        let mut pkcs8 = PKCS8_ED25519_PREFIX.to_vec();
+        pkcs8.extend_from_slice(&seed);
+        assert_eq!(sign(KeyType::TlsPrivateKey, &pkcs8, b"hello"), Ok(raw));
+
+        assert_eq!(sign(KeyType::Symmetric, &seed, b"hello"), Err(SignError::WrongType));
+        assert_eq!(sign(KeyType::TlsPrivateKey, &seed[..31], b"hello"), Err(SignError::UnsupportedKey));
+    }
+}
@@ -0,0 +1,344 @@
+/*
+ * Orion Operating System - mDNS Responder
+ *
+ * Multicast DNS responder and querier (RFC 6762) advertising the services
+ * of this host with DNS-SD records (RFC 6763), so that management tools
+ * find an Orion machine on the local link without configuration. The
+ * management API (_orion-mgmt._tcp, 8443) and the metrics endpoint
+ * (_orion-metrics._tcp, 9100) are advertised from the start; other
+ * servers, such as the NBD server for its exports (_nbd._tcp), register
+ * their instances over IPC.
+ *
+ * The unique names (host and instances) are probed three times 250 ms
+ * apart, then announced twice one second apart; a conflicting answer
+ * renames the loser and starts probing again. Queries are answered once
+ * probing succeeded. Service types browsed through IPC are queried with
+ * an interval doubling from one second to an hour, and every response
+ * heard feeds the cache the BROWSE replies are built from. Registration
+ * and browsing go through orion_mdns::client.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_mdns::client::{encode_discovered, MDNS_EINVAL, MDNS_ENOENT, MDNS_EPERM, MDNS_OK};
+use orion_mdns::{Browser, Destination, MdnsRequest, Message, Responder, Service, MDNS_GROUP, MDNS_PORT};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+use orion_sys::clock_get;
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the records and the query cache
+const HEAP_SIZE: usize = 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod udp;
+
+use udp::UdpSocket;
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 10_000_000;
+
+const NS_PER_MS: u64 = 1_000_000;
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+const PROBE_COUNT: u8 = 3;
+const PROBE_INTERVAL_NS: u64 = 250 * NS_PER_MS;
+const ANNOUNCE_COUNT: u8 = 2;
+const ANNOUNCE_INTERVAL_NS: u64 = NS_PER_SEC;
+
+/// Browse queries back off from the first interval to the last
+const BROWSE_FIRST_INTERVAL_NS: u64 = NS_PER_SEC;
+const BROWSE_MAX_INTERVAL_NS: u64 = 3600 * NS_PER_SEC;
+
+/// Largest datagram read from the socket
+const MAX_PACKET: usize = 9000;
+
+/// Service types browsed at once
+const MAX_BROWSES: usize = 16;
+
+const DEFAULT_HOST: &str = "orion";
+
+// Clocks (mirror of wallclock.h)
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Probes sent so far for the current names
+    Probing(u8),
+    /// Announcements sent so far
+    Announcing(u8),
+    Ready,
+}
+
+/// Service type browsed on behalf of IPC clients
+struct Browse {
+    service_type: String,
+    interval_ns: u64,
+    next_ns: u64,
+}
+
+struct MdnsServer {
+    responder: Responder,
+    browser: Browser,
+    browses: Vec<Browse>,
+    state: State,
+    next_ns: u64,
+    socket: Option<UdpSocket>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl MdnsServer {
+    fn new() -> Self {
+        let mut responder = Responder::new(DEFAULT_HOST, 0);
+        responder.register(Service {
+            instance: "Orion Management".to_string(),
+            service_type: "_orion-mgmt._tcp".to_string(),
+            port: 8443,
+            txt: vec!["path=/api".to_string()],
+        });
+        responder.register(Service {
+            instance: "Orion Metrics".to_string(),
+            service_type: "_orion-metrics._tcp".to_string(),
+            port: 9100,
+            txt: vec!["path=/metrics".to_string()],
+        });
+
+        Self {
+            responder,
+            browser: Browser::new(),
+            browses: Vec::new(),
+            state: State::Probing(0),
+            next_ns: 0,
+            socket: None,
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+
+            let now = monotonic_ns();
+            if self.socket.is_none() && now >= self.next_ns {
+                match UdpSocket::bind_multicast(IpcChannel::connect("net"), MDNS_PORT, MDNS_GROUP) {
+                    Ok(socket) => self.socket = Some(socket),
+                    // Network server not ready, try again in a second
+                    Err(_) => self.next_ns = now + NS_PER_SEC,
+                }
+            }
+            if self.socket.is_some() {
+                self.receive(now);
+                self.advance(now);
+                self.browse(now);
+                self.browser.expire(now);
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn send(&mut self, message: &Message, destination: (u32, u16)) {
+        if let Some(socket) = self.socket.as_mut() {
+            let _ = socket.send_to(destination.0, destination.1, &message.encode());
+        }
+    }
+
+    fn multicast(&mut self, message: &Message) {
+        self.send(message, (MDNS_GROUP, MDNS_PORT));
+    }
+
+    /// Probe the current names again, after a conflict or a change of names
+    fn restart_probing(&mut self, now: u64) {
+        self.state = State::Probing(0);
+        self.next_ns = now;
+    }
+
+    /// Next step of probing and announcing
+    fn advance(&mut self, now: u64) {
+        if now < self.next_ns {
+            return;
+        }
+        match self.state {
+            State::Probing(sent) if sent < PROBE_COUNT => {
+                let probe = self.responder.probe();
+                self.multicast(&probe);
+                self.state = State::Probing(sent + 1);
+                self.next_ns = now + PROBE_INTERVAL_NS;
+            }
+            State::Probing(_) => {
+                // No conflict heard after the last probe: the names are ours
+                self.state = State::Announcing(0);
+            }
+            State::Announcing(sent) if sent < ANNOUNCE_COUNT => {
+                let announcement = self.responder.announcement();
+                self.multicast(&announcement);
+                self.state = State::Announcing(sent + 1);
+                self.next_ns = now + ANNOUNCE_INTERVAL_NS;
+            }
+            State::Announcing(_) => self.state = State::Ready,
+            State::Ready => {}
+        }
+    }
+
+    fn receive(&mut self, now: u64) {
+        loop {
+            let (ip, port, data) = match self.socket.as_mut().map(|socket| socket.recv_from(MAX_PACKET)) {
+                Some(Ok(datagram)) => datagram,
+                _ => return,
+            };
+            let message = match Message::decode(&data) {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+
+            if message.is_response() {
+                // Responses must come from the mDNS port (RFC 6762 section 11)
+                if port != MDNS_PORT {
+                    continue;
+                }
+                let conflicts = self.responder.conflicts(&message);
+                if !conflicts.is_empty() {
+                    for owner in conflicts {
+                        self.responder.rename(owner);
+                    }
+                    self.restart_probing(now);
+                }
+                self.browser.insert(&message, now);
+            } else if self.state == State::Ready {
+                if let Some((response, destination)) = self.responder.respond(&message, port) {
+                    let destination = match destination {
+                        Destination::Unicast => (ip, port),
+                        Destination::Multicast => (MDNS_GROUP, MDNS_PORT),
+                    };
+                    self.send(&response, destination);
+                }
+            }
+        }
+    }
+
+    fn browse(&mut self, now: u64) {
+        for index in 0..self.browses.len() {
+            if now < self.browses[index].next_ns {
+                continue;
+            }
+            let query = self.browser.query(&self.browses[index].service_type, now);
+            self.multicast(&query);
+            let browse = &mut self.browses[index];
+            browse.next_ns = now + browse.interval_ns;
+            browse.interval_ns = (browse.interval_ns * 2).min(BROWSE_MAX_INTERVAL_NS);
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match MdnsRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(MDNS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            MdnsRequest::Browse { .. } => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(MDNS_EPERM, &[]));
+            return;
+        }
+
+        let now = monotonic_ns();
+        let response = match request {
+            MdnsRequest::Register { instance, service_type, port, txt } => {
+                if instance.is_empty() || !service_type.starts_with('_') {
+                    reply(MDNS_EINVAL, &[])
+                } else {
+                    self.responder.register(Service { instance, service_type, port, txt });
+                    self.restart_probing(now);
+                    reply(MDNS_OK, &[])
+                }
+            }
+            MdnsRequest::Unregister { instance, service_type } => {
+                match self.responder.unregister(&instance, &service_type) {
+                    Some(service) => {
+                        if self.state == State::Ready {
+                            let goodbye = self.responder.service_goodbye(&service);
+                            self.multicast(&goodbye);
+                        }
+                        reply(MDNS_OK, &[])
+                    }
+                    None => reply(MDNS_ENOENT, &[]),
+                }
+            }
+            MdnsRequest::Browse { service_type } => {
+                let known = self.browses.iter().any(|browse| browse.service_type == service_type);
+                if !known && self.browses.len() < MAX_BROWSES {
+                    self.browses.push(Browse {
+                        service_type: service_type.clone(),
+                        interval_ns: BROWSE_FIRST_INTERVAL_NS,
+                        next_ns: now,
+                    });
+                }
+                encode_discovered(&self.browser.services(&service_type))
+            }
+            MdnsRequest::SetHost { name, address } => {
+                if name.is_empty() || name.contains('.') {
+                    reply(MDNS_EINVAL, &[])
+                } else {
+                    // Withdraw the old address before probing the new one
+                    if self.state == State::Ready && self.responder.address() != 0 {
+                        let goodbye = self.responder.goodbye();
+                        self.multicast(&goodbye);
+                    }
+                    self.responder.set_host(&name, address);
+                    self.restart_probing(now);
+                    reply(MDNS_OK, &[])
+                }
+            }
+        };
+
+        self.ipc_channel.send(message.sender, &response);
+    }
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = MdnsServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
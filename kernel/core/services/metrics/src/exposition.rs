@@ -0,0 +1,154 @@
+/*
+ * Orion Operating System - Prometheus Text Exposition
+ *
+ * Writer for the Prometheus text format (version 0.0.4): every metric
+ * family is announced with HELP and TYPE lines followed by its samples.
+ * A histogram sample is written as its cumulative buckets, sum and count.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+#[derive(Default)]
+pub struct Exposition {
+    text: String,
+}
+
+fn escape_label(value: &str, out: &mut String) {
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn family(&mut self, name: &str, kind: MetricKind, help: &str) -> &mut Self {
+        let kind = match kind {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+        };
+        self.text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+        self
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: u64) -> &mut Self {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            self.text.push('{');
+            for (index, (label, value)) in labels.iter().enumerate() {
+                if index > 0 {
+                    self.text.push(',');
+                }
+                self.text.push_str(label);
+                self.text.push_str("=\"");
+                escape_label(value, &mut self.text);
+                self.text.push('"');
+            }
+            self.text.push('}');
+        }
+        self.text.push_str(&format!(" {}\n", value));
+        self
+    }
+
+    /// Histogram sample: `buckets[i]` counts the values up to `bounds[i]`
+    /// and above the bound before, the last bucket those above every bound
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        bounds: &[u64],
+        buckets: &[u64],
+        sum: u64,
+    ) -> &mut Self {
+        let bucket_name = format!("{}_bucket", name);
+        let mut count = 0;
+        for (index, bucket) in buckets.iter().enumerate() {
+            count += bucket;
+            let le = bounds.get(index).map_or_else(|| String::from("+Inf"), |bound| format!("{}", bound));
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", &le));
+            self.sample(&bucket_name, &bucket_labels, count);
+        }
+        self.sample(&format!("{}_sum", name), labels, sum).sample(&format!("{}_count", name), labels, count)
+    }
+
+    /// Family with a single unlabelled sample
+    pub fn single(&mut self, name: &str, kind: MetricKind, help: &str, value: u64) -> &mut Self {
+        self.family(name, kind, help).sample(name, &[], value)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.text.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_families() {
+        let mut exposition = Exposition::new();
+        exposition
+            .single("orion_entropy_seeded", MetricKind::Gauge, "DRBG seeded", 1)
+            .family("orion_up", MetricKind::Gauge, "Server answered its status request")
+            .sample("orion_up", &[("server", "io")], 1)
+            .sample("orion_up", &[("server", "a\"b\\c\n"), ("x", "y")], 0);
+
+        assert_eq!(
+            exposition.into_bytes(),
+            b"# HELP orion_entropy_seeded DRBG seeded\n# TYPE orion_entropy_seeded gauge\norion_entropy_seeded 1\n\
+              # HELP orion_up Server answered its status request\n# TYPE orion_up gauge\n\
+              orion_up{server=\"io\"} 1\norion_up{server=\"a\\\"b\\\\c\\n\",x=\"y\"} 0\n"
+        );
+    }
+
+    #[test]
+    fn test_formats_cumulative_histograms() {
+        let mut exposition = Exposition::new();
+        exposition.family("orion_ipc_wait_nanoseconds", MetricKind::Histogram, "Queueing").histogram(
+            "orion_ipc_wait_nanoseconds",
+            &[("server", "fs")],
+            &[1000, 10000],
+            &[3, 0, 2],
+            25000,
+        );
+
+        assert_eq!(
+            exposition.into_bytes(),
+            b"# HELP orion_ipc_wait_nanoseconds Queueing\n# TYPE orion_ipc_wait_nanoseconds histogram\n\
+              orion_ipc_wait_nanoseconds_bucket{server=\"fs\",le=\"1000\"} 3\n\
+              orion_ipc_wait_nanoseconds_bucket{server=\"fs\",le=\"10000\"} 3\n\
+              orion_ipc_wait_nanoseconds_bucket{server=\"fs\",le=\"+Inf\"} 5\n\
+              orion_ipc_wait_nanoseconds_sum{server=\"fs\"} 25000\n\
+              orion_ipc_wait_nanoseconds_count{server=\"fs\"} 5\n"
+        );
+    }
+}
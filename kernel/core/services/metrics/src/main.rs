@@ -0,0 +1,417 @@
+/*
+ * Orion Operating System - Metrics Exporter
+ *
+ * Serves the management HTTP endpoints on top of orion_http:
+ *
+ *   GET /metrics              Prometheus text exposition of the servers' counters
+ *   GET /metrics/ipc/:server  IPC endpoint statistics of one server
+ *   GET /healthz              200 when every server is ready, 503 otherwise
+ *   GET /healthz/live         200 unless a server has to be restarted
+ *
+ * Values are gathered when a request arrives by calling the STATUS
+ * operation of each server, and from the kernel for the counters it keeps
+ * itself (same-page merging, IPC endpoints); nothing is cached between
+ * scrapes. IPC endpoints are labelled with the name and PID of the
+ * process serving them, so a server's queue depth, queueing and handler
+ * latency histograms and drops can be told apart from the others'. The
+ * health endpoints answer the report of the health server as JSON, with
+ * the checks of every server, for load balancers and monitoring. The
+ * HTTP listener is a socket of the network server, polled between IPC
+ * checks.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_health::{HealthClient, HealthReport, HealthState, ServerHealth};
+use orion_http::json::array;
+use orion_http::socket::{NetChannel, NetListener};
+use orion_http::{ObjectWriter, Params, Request, Response, Router, Server, ServerConfig, ServerStats, Status};
+use orion_ipc::IpcChannel;
+use orion_sys::{ipc_stats, ksm_stats, proc_info, IpcPortStats, KsmStats, ProcInfo};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod exposition;
+
+use exposition::{Exposition, MetricKind, CONTENT_TYPE};
+
+/// Port of the HTTP listener (the usual Prometheus exporter port)
+const HTTP_PORT: u16 = 9100;
+const HTTP_BACKLOG: u16 = 16;
+
+/// Pause between two polls of the HTTP connections
+const POLL_INTERVAL_NS: u64 = 10_000_000;
+
+// STATUS opcodes of the probed servers
+const ENTROPY_OP_STATUS: u32 = 4;
+const IO_OP_STATUS: u32 = 6;
+
+// Thermal server zone list (services/thermal/src/protocol.rs)
+const THERMAL_OP_LIST_ZONES: u32 = 5;
+const THERMAL_ZONE_RECORD_SIZE: usize = 36;
+const THERMAL_NAME_SIZE: usize = 16;
+
+/// Upper bounds of the IPC latency buckets but the last (ipc_stats.h)
+const IPC_BUCKET_BOUNDS_NS: [u64; 7] = [1_000, 10_000, 100_000, 1_000_000, 10_000_000, 100_000_000, 1_000_000_000];
+
+/// IPC channel to the network server used by the HTTP listener
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// IPC channel to the health server
+struct HealthIpc(IpcChannel);
+
+impl orion_health::Transport for HealthIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as u64)
+        .unwrap_or(0)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .unwrap_or(0)
+}
+
+/// Servers probed for metrics and health
+struct Sources {
+    entropy: IpcChannel,
+    io: IpcChannel,
+    thermal: IpcChannel,
+    health: HealthClient<HealthIpc>,
+    http: ServerStats,
+    scrapes: u64,
+}
+
+impl Sources {
+    /// STATUS payload of a server, None if it did not answer or failed
+    fn status(channel: &mut IpcChannel, opcode: u32, min_len: usize) -> Option<Vec<u8>> {
+        let response = channel.call(&opcode.to_le_bytes()).ok()?;
+        if response.len() < 4 + min_len || response[..4] != 0i32.to_le_bytes() {
+            return None;
+        }
+        Some(response[4..].to_vec())
+    }
+
+    fn entropy_status(&mut self) -> Option<Vec<u8>> {
+        Self::status(&mut self.entropy, ENTROPY_OP_STATUS, 28)
+    }
+
+    fn io_status(&mut self) -> Option<Vec<u8>> {
+        Self::status(&mut self.io, IO_OP_STATUS, 32)
+    }
+
+    fn thermal_zones(&mut self) -> Option<Vec<u8>> {
+        Self::status(&mut self.thermal, THERMAL_OP_LIST_ZONES, 0)
+    }
+
+    fn ksm(&mut self) -> Option<KsmStats> {
+        let mut stats = KsmStats::default();
+        ksm_stats(&mut stats).ok().map(|_| stats)
+    }
+}
+
+fn process_name(info: &ProcInfo) -> String {
+    let length = info.name.iter().position(|&byte| byte == 0).unwrap_or(info.name.len());
+    String::from(core::str::from_utf8(&info.name[..length]).unwrap_or("?"))
+}
+
+/// Statistics of every IPC port with the name of the process serving it
+fn ipc_endpoints() -> Vec<(String, IpcPortStats)> {
+    let mut names = Vec::new();
+    let mut after = 0;
+    let mut info = ProcInfo::default();
+    while let Ok(pid) = proc_info(after, &mut info) {
+        after = pid;
+        names.push((pid, process_name(&info)));
+    }
+
+    let mut endpoints = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let mut stats = IpcPortStats::default();
+        match ipc_stats(cursor, &mut stats) {
+            Ok(next) => cursor = next,
+            Err(_) => return endpoints,
+        }
+        let name = names.iter().find(|(pid, _)| *pid == stats.owner_pid).map(|(_, name)| name.clone());
+        endpoints.push((name.unwrap_or_else(|| String::from("?")), stats));
+    }
+}
+
+fn export_entropy(status: &[u8]) -> Vec<u8> {
+    let mut out = Exposition::new();
+    out.single("orion_entropy_seeded", MetricKind::Gauge, "Whether the DRBG has been seeded", status[0] as u64)
+        .single("orion_entropy_pool_bits", MetricKind::Gauge, "Entropy credited to the input pool", read_u32(status, 4))
+        .single("orion_entropy_reseeds_total", MetricKind::Counter, "DRBG reseeds", read_u64(status, 8))
+        .single("orion_entropy_served_bytes_total", MetricKind::Counter, "Random bytes served", read_u64(status, 16))
+        .single("orion_entropy_pending_requests", MetricKind::Gauge, "Callers waiting for the initial seed", read_u32(status, 24));
+    out.into_bytes()
+}
+
+fn export_io(status: &[u8]) -> Vec<u8> {
+    let mut out = Exposition::new();
+    out.single("orion_io_policy_enforcing", MetricKind::Gauge, "Whether unsigned drivers are refused", (read_u32(status, 0) == 0) as u64)
+        .single("orion_io_trusted_keys", MetricKind::Gauge, "Enrolled driver signing keys", read_u32(status, 8))
+        .single("orion_io_devices", MetricKind::Gauge, "Registered devices", read_u32(status, 12))
+        .family("orion_io_driver_loads_total", MetricKind::Counter, "Driver load requests by outcome")
+        .sample("orion_io_driver_loads_total", &[("result", "allowed")], read_u64(status, 16))
+        .sample("orion_io_driver_loads_total", &[("result", "denied")], read_u64(status, 24));
+    out.into_bytes()
+}
+
+fn export_thermal(zones: &[u8]) -> Vec<u8> {
+    let zones: Vec<(&str, &[u8])> = zones
+        .chunks_exact(THERMAL_ZONE_RECORD_SIZE)
+        .map(|record| {
+            let name = &record[..THERMAL_NAME_SIZE];
+            let name = &name[..name.iter().position(|byte| *byte == 0).unwrap_or(THERMAL_NAME_SIZE)];
+            (core::str::from_utf8(name).unwrap_or("?"), record)
+        })
+        .collect();
+
+    let mut out = Exposition::new();
+    out.family("orion_thermal_temperature_millicelsius", MetricKind::Gauge, "Temperature of the thermal zone");
+    // Zones without a fresh reading have no temperature to show
+    for (zone, record) in zones.iter().filter(|(_, record)| read_u32(record, 20) != 0) {
+        let temp_mc = read_u32(record, 24) as u32 as i32;
+        out.sample("orion_thermal_temperature_millicelsius", &[("zone", zone)], temp_mc.max(0) as u64);
+    }
+    out.family("orion_thermal_trip_level", MetricKind::Gauge, "Most severe tripped trip: 0 none, 1 passive to 4 critical");
+    for (zone, record) in &zones {
+        out.sample("orion_thermal_trip_level", &[("zone", zone)], read_u32(record, 28));
+    }
+    out.into_bytes()
+}
+
+fn export_ksm(stats: &KsmStats) -> Vec<u8> {
+    let mut out = Exposition::new();
+    out.single("orion_ksm_spaces", MetricKind::Gauge, "Address spaces with mergeable areas", stats.spaces)
+        .single("orion_ksm_pages_shared", MetricKind::Gauge, "Merged frames still mapped", stats.pages_shared)
+        .single("orion_ksm_pages_sharing", MetricKind::Gauge, "Pages saved by merging", stats.pages_sharing)
+        .single("orion_ksm_pages_scanned_total", MetricKind::Counter, "Pages looked at by the scanner", stats.pages_scanned)
+        .single("orion_ksm_full_scans_total", MetricKind::Counter, "Passes over every mergeable area", stats.full_scans)
+        .single("orion_ksm_merges_total", MetricKind::Counter, "Pages replaced by a merged frame", stats.merges)
+        .single("orion_ksm_volatile_pages_total", MetricKind::Counter, "Candidates skipped for having changed", stats.volatile_pages)
+        .single("orion_ksm_released_total", MetricKind::Counter, "Merged frames no longer shared", stats.released);
+    out.into_bytes()
+}
+
+/// Family exported for every IPC endpoint: name, kind, help and value
+type IpcFamily = (&'static str, MetricKind, &'static str, fn(&IpcPortStats) -> u64);
+
+/// server, pid and port labels of an IPC endpoint
+fn ipc_labels(labels: &[String; 3]) -> [(&str, &str); 3] {
+    [("server", &labels[0]), ("pid", &labels[1]), ("port", &labels[2])]
+}
+
+fn export_ipc(endpoints: &[(String, IpcPortStats)]) -> Vec<u8> {
+    let endpoints: Vec<([String; 3], &IpcPortStats)> = endpoints
+        .iter()
+        .map(|(server, stats)| ([server.clone(), format!("{}", stats.owner_pid), format!("{}", stats.port)], stats))
+        .collect();
+    let families: [IpcFamily; 7] = [
+        ("orion_ipc_messages_total", MetricKind::Counter, "Messages and calls received by the endpoint", |s| s.messages),
+        ("orion_ipc_bytes_total", MetricKind::Counter, "Bytes carried by the endpoint, requests and replies", |s| s.bytes),
+        ("orion_ipc_queue_depth", MetricKind::Gauge, "Messages and calls waiting for the server", |s| s.queued),
+        ("orion_ipc_queue_depth_max", MetricKind::Gauge, "Most messages and calls waiting at once", |s| s.queued_max),
+        ("orion_ipc_dropped_total", MetricKind::Counter, "Messages and calls never served", |s| s.dropped),
+        ("orion_ipc_wait_max_nanoseconds", MetricKind::Gauge, "Longest wait for the server", |s| s.wait_ns_max),
+        ("orion_ipc_handler_max_nanoseconds", MetricKind::Gauge, "Longest time the server spent on a request", |s| s.handler_ns_max),
+    ];
+
+    let mut out = Exposition::new();
+    for (name, kind, help, value) in families {
+        out.family(name, kind, help);
+        for (labels, stats) in &endpoints {
+            out.sample(name, &ipc_labels(labels), value(stats));
+        }
+    }
+    out.family("orion_ipc_wait_nanoseconds", MetricKind::Histogram, "Time requests waited before the server took them");
+    for (labels, stats) in &endpoints {
+        out.histogram("orion_ipc_wait_nanoseconds", &ipc_labels(labels), &IPC_BUCKET_BOUNDS_NS, &stats.wait_buckets, stats.wait_ns_total);
+    }
+    out.family("orion_ipc_handler_nanoseconds", MetricKind::Histogram, "Time the server spent on a request");
+    for (labels, stats) in &endpoints {
+        out.histogram("orion_ipc_handler_nanoseconds", &ipc_labels(labels), &IPC_BUCKET_BOUNDS_NS, &stats.handler_buckets, stats.handler_ns_total);
+    }
+    out.into_bytes()
+}
+
+fn export_http(stats: &ServerStats, scrapes: u64) -> Vec<u8> {
+    let mut out = Exposition::new();
+    out.single("orion_http_connections_total", MetricKind::Counter, "Accepted management connections", stats.connections_accepted)
+        .single("orion_http_requests_total", MetricKind::Counter, "Management HTTP requests", stats.requests)
+        .single("orion_http_bad_requests_total", MetricKind::Counter, "Unparseable management HTTP requests", stats.bad_requests)
+        .single("orion_metrics_scrapes_total", MetricKind::Counter, "Requests for /metrics", scrapes);
+    out.into_bytes()
+}
+
+fn metrics(sources: &mut Sources, _request: &Request, _params: &Params) -> Response {
+    sources.scrapes += 1;
+    let entropy = sources.entropy_status();
+    let io = sources.io_status();
+    let thermal = sources.thermal_zones();
+    let ksm = sources.ksm();
+    let endpoints = ipc_endpoints();
+
+    let mut up = Exposition::new();
+    up.family("orion_up", MetricKind::Gauge, "Whether the server answered its status request")
+        .sample("orion_up", &[("server", "entropy")], entropy.is_some() as u64)
+        .sample("orion_up", &[("server", "io")], io.is_some() as u64)
+        .sample("orion_up", &[("server", "thermal")], thermal.is_some() as u64);
+
+    // One chunk per server so the body is streamed as it is produced
+    let mut pieces = vec![up.into_bytes()];
+    pieces.extend(entropy.as_deref().map(export_entropy));
+    pieces.extend(io.as_deref().map(export_io));
+    pieces.extend(thermal.as_deref().map(export_thermal));
+    pieces.extend(ksm.as_ref().map(export_ksm));
+    pieces.push(export_ipc(&endpoints));
+    pieces.push(export_http(&sources.http, sources.scrapes));
+
+    Response::chunked(Status::OK, CONTENT_TYPE, pieces.into_iter())
+}
+
+/// IPC endpoint statistics of the server named in the path only
+fn metrics_ipc(_sources: &mut Sources, _request: &Request, params: &Params) -> Response {
+    let server = params.get("server").unwrap_or("");
+    let endpoints: Vec<_> = ipc_endpoints().into_iter().filter(|(name, _)| name == server).collect();
+    if endpoints.is_empty() {
+        return Response::text(Status::NOT_FOUND, "no IPC endpoint served by that server\n");
+    }
+    Response::chunked(Status::OK, CONTENT_TYPE, vec![export_ipc(&endpoints)].into_iter())
+}
+
+fn server_json(server: &ServerHealth) -> String {
+    let checks = server.checks.iter().map(|check| {
+        ObjectWriter::new()
+            .string("name", &check.name)
+            .string("probe", check.probe.as_str())
+            .boolean("passed", check.passed)
+            .string("detail", &check.detail)
+            .finish()
+    });
+    ObjectWriter::new()
+        .string("name", &server.name)
+        .string("status", server.state.as_str())
+        .unsigned("failures", server.failures as u64)
+        .raw("checks", &array(checks))
+        .finish()
+}
+
+/// The health report as JSON, 200 when `healthy` holds for its state
+fn health_response(report: Option<HealthReport>, healthy: fn(HealthState) -> bool) -> Response {
+    let (status, json) = match report {
+        Some(report) => {
+            let json = ObjectWriter::new()
+                .string("status", report.state.as_str())
+                .boolean("live", report.state.live())
+                .boolean("ready", report.state.ready())
+                .raw("servers", &array(report.servers.iter().map(server_json)))
+                .finish();
+            (if healthy(report.state) { Status::OK } else { Status::SERVICE_UNAVAILABLE }, json)
+        }
+        // Without the health server nothing vouches for the others
+        None => (
+            Status::SERVICE_UNAVAILABLE,
+            ObjectWriter::new()
+                .string("status", "unavailable")
+                .boolean("live", false)
+                .boolean("ready", false)
+                .raw("servers", "[]")
+                .finish(),
+        ),
+    };
+    Response::json(status, json).header("Cache-Control", "no-store")
+}
+
+fn healthz(sources: &mut Sources, _request: &Request, _params: &Params) -> Response {
+    health_response(sources.health.status().ok(), HealthState::ready)
+}
+
+fn healthz_live(sources: &mut Sources, _request: &Request, _params: &Params) -> Response {
+    health_response(sources.health.status().ok(), HealthState::live)
+}
+
+struct MetricsServer {
+    http: Server<NetListener<NetIpc>>,
+    router: Router<Sources>,
+    sources: Sources,
+}
+
+impl MetricsServer {
+    fn new() -> Option<Self> {
+        let listener = NetListener::bind(NetIpc(IpcChannel::connect("net")), 0, HTTP_PORT, HTTP_BACKLOG).ok()?;
+
+        Some(Self {
+            http: Server::new(listener, ServerConfig::default()),
+            router: Router::new()
+                .get("/metrics", metrics)
+                .get("/metrics/ipc/:server", metrics_ipc)
+                .get("/healthz", healthz)
+                .get("/healthz/live", healthz_live),
+            sources: Sources {
+                entropy: IpcChannel::connect("entropy"),
+                io: IpcChannel::connect("io"),
+                thermal: IpcChannel::connect("thermal"),
+                health: HealthClient::new(HealthIpc(IpcChannel::connect("health"))),
+                http: ServerStats::default(),
+                scrapes: 0,
+            },
+        })
+    }
+
+    fn run(&mut self) {
+        loop {
+            self.sources.http = self.http.stats();
+            self.http.poll(&self.router, &mut self.sources);
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+}
+
+fn main() {
+    // Without the network server there is nothing to export to
+    if let Some(mut server) = MetricsServer::new() {
+        server.run();
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,219 @@
+/*
+ * Orion Operating System - Management Alerts
+ *
+ * Alerts are raised when a watched condition changes: a server stops
+ * answering, an interface goes down, a volume group fails or a snapshot
+ * is invalidated. Each condition is identified by a key and reported once
+ * when it appears and once when it clears. The log keeps the most recent
+ * alerts with increasing sequence numbers; event streams follow it with
+ * a cursor, so a client reconnecting with Last-Event-ID misses nothing
+ * that is still in the log.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use orion_http::ObjectWriter;
+
+/// Alerts kept for streams that fall behind or reconnect
+pub const ALERT_LOG_SIZE: usize = 128;
+
+/// Empty polls of a stream between two keep-alive comments
+pub const KEEPALIVE_POLLS: u32 = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Resolved,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Resolved => "resolved",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub sequence: u64,
+    pub severity: Severity,
+    /// Condition key, such as "interface:eth0:down"
+    pub key: String,
+    pub message: String,
+}
+
+impl Alert {
+    pub fn to_json(&self) -> String {
+        ObjectWriter::new()
+            .unsigned("id", self.sequence)
+            .string("severity", self.severity.as_str())
+            .string("key", &self.key)
+            .string("message", &self.message)
+            .finish()
+    }
+}
+
+pub struct AlertLog {
+    entries: VecDeque<Alert>,
+    active: Vec<String>,
+    next_sequence: u64,
+}
+
+pub type SharedAlertLog = Rc<RefCell<AlertLog>>;
+
+impl AlertLog {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            active: Vec::new(),
+            next_sequence: 1,
+        }
+    }
+
+    fn push(&mut self, severity: Severity, key: &str, message: String) {
+        if self.entries.len() >= ALERT_LOG_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Alert {
+            sequence: self.next_sequence,
+            severity,
+            key: String::from(key),
+            message,
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Record the current state of a condition; only changes are logged
+    pub fn update(&mut self, key: &str, failing: bool, severity: Severity, message: &str) {
+        let position = self.active.iter().position(|active| active == key);
+        match (failing, position) {
+            (true, None) => {
+                self.active.push(String::from(key));
+                self.push(severity, key, String::from(message));
+            }
+            (false, Some(index)) => {
+                self.active.swap_remove(index);
+                self.push(Severity::Resolved, key, format!("{} (cleared)", message));
+            }
+            _ => {}
+        }
+    }
+
+    /// Conditions currently failing
+    pub fn active(&self) -> &[String] {
+        &self.active
+    }
+
+    /// Sequence number the next alert will get
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Alerts with a sequence number of at least `cursor`
+    pub fn since(&self, cursor: u64) -> impl Iterator<Item = &Alert> {
+        self.entries.iter().filter(move |alert| alert.sequence >= cursor)
+    }
+}
+
+/// Server-sent events body following an alert log
+pub struct AlertStream {
+    log: SharedAlertLog,
+    cursor: u64,
+    idle_polls: u32,
+}
+
+impl AlertStream {
+    /// Stream the alerts from sequence `cursor` on
+    pub fn new(log: SharedAlertLog, cursor: u64) -> Self {
+        Self { log, cursor, idle_polls: 0 }
+    }
+}
+
+impl Iterator for AlertStream {
+    type Item = Vec<u8>;
+
+    /// Never ends; an empty piece tells the server nothing is ready yet
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let mut events = String::new();
+        for alert in self.log.borrow().since(self.cursor) {
+            events.push_str(&format!("id: {}\nevent: alert\ndata: {}\n\n", alert.sequence, alert.to_json()));
+            self.cursor = alert.sequence + 1;
+        }
+
+        if events.is_empty() {
+            self.idle_polls += 1;
+            if self.idle_polls < KEEPALIVE_POLLS {
+                return Some(Vec::new());
+            }
+            // Comment line: keeps proxies from timing out and notices clients gone
+            events.push_str(": keep-alive\n\n");
+        }
+        self.idle_polls = 0;
+        Some(events.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logs_condition_changes() {
+        let mut log = AlertLog::new();
+        log.update("interface:eth0:down", true, Severity::Warning, "interface eth0 is down");
+        log.update("interface:eth0:down", true, Severity::Warning, "interface eth0 is down");
+        log.update("server:io:unavailable", false, Severity::Critical, "io server is not answering");
+        assert_eq!(log.active(), &[String::from("interface:eth0:down")]);
+        assert_eq!(log.since(0).count(), 1);
+
+        log.update("interface:eth0:down", false, Severity::Warning, "interface eth0 is down");
+        let alerts: Vec<&Alert> = log.since(2).collect();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::Resolved);
+        assert_eq!(alerts[0].message, "interface eth0 is down (cleared)");
+        assert!(log.active().is_empty());
+
+        for index in 0..ALERT_LOG_SIZE as u64 {
+            let key = format!("snapshot:{}:invalid", index);
+            log.update(&key, true, Severity::Warning, "snapshot invalid");
+        }
+        assert_eq!(log.since(0).count(), ALERT_LOG_SIZE);
+        assert_eq!(log.since(0).next().unwrap().sequence, 3);
+    }
+
+    #[test]
+    fn test_streams_events() {
+        let log = Rc::new(RefCell::new(AlertLog::new()));
+        let mut stream = AlertStream::new(log.clone(), log.borrow().next_sequence());
+        assert_eq!(stream.next(), Some(Vec::new()));
+
+        log.borrow_mut().update("vg:vg0:failed", true, Severity::Critical, "volume group vg0 failed");
+        let event = String::from_utf8(stream.next().unwrap()).unwrap();
+        assert_eq!(
+            event,
+            "id: 1\nevent: alert\ndata: {\"id\":1,\"severity\":\"critical\",\"key\":\"vg:vg0:failed\",\
+             \"message\":\"volume group vg0 failed\"}\n\n"
+        );
+        assert_eq!(stream.next(), Some(Vec::new()));
+
+        for _ in 2..KEEPALIVE_POLLS {
+            assert_eq!(stream.next(), Some(Vec::new()));
+        }
+        assert_eq!(stream.next(), Some(Vec::from(&b": keep-alive\n\n"[..])));
+    }
+}
@@ -0,0 +1,518 @@
+/*
+ * Orion Operating System - Management REST API
+ *
+ * Handlers of the /api/v1 endpoints. Every request must carry a bearer
+ * token holding the scope of the endpoint: 401 without a valid token, 403
+ * when the scope is missing. Bodies are JSON in both directions, except
+ * the alert stream which uses server-sent events.
+ *
+ * Storage ACLs are addressed by kind, "pools" or "volumes", and name. An
+ * entry is {"principal": "everyone" | "capability" | "process", "id",
+ * "allow": [rights], "deny": [rights]} with rights among "read", "write",
+ * "snapshot" and "admin". A PUT replaces every entry and must carry the
+ * generation its GET returned: 409 tells the caller someone else changed
+ * the list in between.
+ *
+ * Crashes are the core dumps kept by the crash dump server, listed by
+ * component and from a realtime clock value in nanoseconds on; a single
+ * crash is served as the raw dump, as application/octet-stream.
+ *
+ *   GET    /api/v1/drivers                 inventory:read
+ *   GET    /api/v1/interfaces              network:read
+ *   PUT    /api/v1/interfaces/:name        network:write  {"up", "mtu"}
+ *   GET    /api/v1/pools                   storage:read
+ *   GET    /api/v1/pools/:pool/snapshots   storage:read
+ *   POST   /api/v1/snapshots               storage:write  {"name", "origin", "size"}
+ *   DELETE /api/v1/snapshots/:name         storage:write
+ *   GET    /api/v1/acls/:kind/:name        storage:admin
+ *   PUT    /api/v1/acls/:kind/:name        storage:admin  {"generation", "entries"}
+ *   GET    /api/v1/crashes                 storage:read   ?component=&since=
+ *   GET    /api/v1/crashes/:id             storage:read
+ *   DELETE /api/v1/crashes/:id             storage:write
+ *   GET    /api/v1/alerts                  alerts:read
+ *   GET    /api/v1/alerts/stream           alerts:read
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::format;
+use alloc::string::String;
+
+use orion_http::json::array;
+use orion_http::{ObjectWriter, Params, Request, Response, Router, Status, Value};
+
+use crate::alerts::{AlertStream, Severity, SharedAlertLog};
+use crate::backends::*;
+use crate::protocol::{STATUS_EINVAL, STATUS_ENOENT, STATUS_EPERM};
+use crate::tokens::*;
+
+const STATUS_EACCES: i32 = -13;
+const STATUS_EEXIST: i32 = -17;
+const STATUS_ESTALE: i32 = -116;
+
+pub struct Api {
+    pub tokens: TokenStore,
+    pub alerts: SharedAlertLog,
+    pub backends: Backends,
+    /// Requests rejected for a missing token or scope
+    pub denied: u64,
+}
+
+pub fn router() -> Router<Api> {
+    Router::new()
+        .get("/api/v1/drivers", drivers)
+        .get("/api/v1/interfaces", interfaces)
+        .put("/api/v1/interfaces/:name", configure_interface)
+        .get("/api/v1/pools", pools)
+        .get("/api/v1/pools/:pool/snapshots", snapshots)
+        .post("/api/v1/snapshots", create_snapshot)
+        .delete("/api/v1/snapshots/:name", remove_snapshot)
+        .get("/api/v1/acls/:kind/:name", acl)
+        .put("/api/v1/acls/:kind/:name", set_acl)
+        .get("/api/v1/crashes", crashes)
+        .get("/api/v1/crashes/:id", crash)
+        .delete("/api/v1/crashes/:id", remove_crash)
+        .get("/api/v1/alerts", alerts)
+        .get("/api/v1/alerts/stream", alert_stream)
+}
+
+fn error(status: Status, message: &str) -> Response {
+    Response::json(status, ObjectWriter::new().string("error", message).finish())
+}
+
+/// Response for a failed backend call
+fn backend_error(status: i32) -> Response {
+    match status {
+        STATUS_ENOENT => error(Status::NOT_FOUND, "not found"),
+        STATUS_EINVAL => error(Status::BAD_REQUEST, "rejected by the server"),
+        STATUS_EEXIST => error(Status::CONFLICT, "already exists"),
+        STATUS_ESTALE => error(Status::CONFLICT, "changed since it was read"),
+        STATUS_EACCES => error(Status::FORBIDDEN, "denied by the storage access list"),
+        STATUS_EPERM => error(Status::INTERNAL_SERVER_ERROR, "management server lacks the rights"),
+        STATUS_EFBIG => error(Status::INTERNAL_SERVER_ERROR, "too large to be served, fetch it with orion-storagectl"),
+        _ => error(Status::SERVICE_UNAVAILABLE, "server unavailable"),
+    }
+}
+
+fn authorize(api: &mut Api, request: &Request, scope: u32) -> Result<(), Response> {
+    match api.tokens.authorize(request.header("Authorization"), scope) {
+        Ok(_) => Ok(()),
+        Err(failure) => {
+            api.denied += 1;
+            Err(match failure {
+                AuthError::Unauthenticated => {
+                    error(Status::UNAUTHORIZED, "missing or invalid token").header("WWW-Authenticate", "Bearer")
+                }
+                AuthError::Forbidden => error(Status::FORBIDDEN, "token lacks the required scope"),
+            })
+        }
+    }
+}
+
+fn json_body(request: &Request) -> Result<Value, Response> {
+    match Value::parse(&request.body) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        _ => Err(error(Status::BAD_REQUEST, "body must be a JSON object")),
+    }
+}
+
+fn drivers(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_INVENTORY_READ) {
+        return response;
+    }
+    let devices = match api.backends.devices() {
+        Ok(devices) => devices,
+        Err(status) => return backend_error(status),
+    };
+
+    let items = devices.iter().map(|device| {
+        let object = ObjectWriter::new()
+            .unsigned("handle", device.handle)
+            .string("vendor_id", &format!("{:04x}", device.vendor_id))
+            .string("device_id", &format!("{:04x}", device.device_id));
+        let object = match device.driver_pid {
+            Some(pid) => object.string("driver", &device.driver).unsigned("driver_pid", pid),
+            None => object.null("driver").null("driver_pid"),
+        };
+        object.finish()
+    });
+    Response::json(Status::OK, ObjectWriter::new().raw("devices", &array(items)).finish())
+}
+
+fn state_name(state: u32) -> &'static str {
+    match state {
+        IFACE_STATE_DOWN => "down",
+        IFACE_STATE_UP => "up",
+        IFACE_STATE_RUNNING => "running",
+        IFACE_STATE_ERROR => "error",
+        _ => "other",
+    }
+}
+
+fn interfaces(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_NETWORK_READ) {
+        return response;
+    }
+    let interfaces = match api.backends.interfaces() {
+        Ok(interfaces) => interfaces,
+        Err(status) => return backend_error(status),
+    };
+
+    const COUNTERS: [&str; 8] = [
+        "rx_packets", "tx_packets", "rx_bytes", "tx_bytes", "rx_errors", "tx_errors", "rx_dropped", "tx_dropped",
+    ];
+    let items = interfaces.iter().map(|iface| {
+        let mac = iface.mac;
+        let mut stats = ObjectWriter::new();
+        for (name, value) in COUNTERS.iter().zip(iface.counters.iter()) {
+            stats = stats.unsigned(name, *value);
+        }
+        ObjectWriter::new()
+            .string("name", &iface.name)
+            .unsigned("type", iface.kind as u64)
+            .string("state", state_name(iface.state))
+            .unsigned("mtu", iface.mtu as u64)
+            .string(
+                "mac",
+                &format!("{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]),
+            )
+            .raw("stats", &stats.finish())
+            .finish()
+    });
+    Response::json(Status::OK, ObjectWriter::new().raw("interfaces", &array(items)).finish())
+}
+
+fn configure_interface(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_NETWORK_WRITE) {
+        return response;
+    }
+    let body = match json_body(request) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    let mut change = InterfaceChange::default();
+    if let Some(up) = body.get("up") {
+        match up.as_bool() {
+            Some(up) => change.up = Some(up),
+            None => return error(Status::BAD_REQUEST, "\"up\" must be a boolean"),
+        }
+    }
+    if let Some(mtu) = body.get("mtu") {
+        match mtu.as_u64().filter(|mtu| (IFACE_MIN_MTU as u64..=IFACE_MAX_MTU as u64).contains(mtu)) {
+            Some(mtu) => change.mtu = Some(mtu as u32),
+            None => return error(Status::BAD_REQUEST, "\"mtu\" is out of range"),
+        }
+    }
+    if change == InterfaceChange::default() {
+        return error(Status::BAD_REQUEST, "nothing to change");
+    }
+
+    match api.backends.configure_interface(params.get("name").unwrap_or(""), change) {
+        Ok(()) => Response::new(Status::NO_CONTENT),
+        Err(status) => backend_error(status),
+    }
+}
+
+fn pools(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_READ) {
+        return response;
+    }
+    let pools = match api.backends.pools() {
+        Ok(pools) => pools,
+        Err(status) => return backend_error(status),
+    };
+
+    let items = pools.iter().map(|pool| {
+        ObjectWriter::new()
+            .string("name", &pool.name)
+            .unsigned("size", pool.size)
+            .unsigned("free", pool.free)
+            .boolean("failed", pool.state == POOL_STATE_FAILED)
+            .unsigned("volumes", pool.volumes as u64)
+            .finish()
+    });
+    Response::json(Status::OK, ObjectWriter::new().raw("pools", &array(items)).finish())
+}
+
+fn snapshots(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_READ) {
+        return response;
+    }
+    let snapshots = match api.backends.snapshots(params.get("pool").unwrap_or("")) {
+        Ok(snapshots) => snapshots,
+        Err(status) => return backend_error(status),
+    };
+
+    let items = snapshots.iter().map(|snapshot| {
+        ObjectWriter::new()
+            .string("name", &snapshot.name)
+            .string("origin", &snapshot.origin)
+            .unsigned("size", snapshot.size)
+            .boolean("valid", snapshot.status != SNAPSHOT_STATUS_INVALID)
+            .finish()
+    });
+    Response::json(Status::OK, ObjectWriter::new().raw("snapshots", &array(items)).finish())
+}
+
+fn create_snapshot(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_WRITE) {
+        return response;
+    }
+    let body = match json_body(request) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    let name = body.get("name").and_then(Value::as_str).filter(|name| !name.is_empty() && !name.contains('/'));
+    let origin = body.get("origin").and_then(Value::as_str);
+    let size = body.get("size").and_then(Value::as_u64).filter(|size| *size > 0);
+    let (name, origin, size) = match (name, origin, size) {
+        (Some(name), Some(origin), Some(size)) => (name, origin, size),
+        _ => return error(Status::BAD_REQUEST, "expected \"name\", \"origin\" and a positive \"size\""),
+    };
+
+    match api.backends.create_snapshot(name, origin, size) {
+        Ok(()) => Response::new(Status::CREATED).header("Location", &format!("/api/v1/snapshots/{}", name)),
+        Err(status) => backend_error(status),
+    }
+}
+
+fn remove_snapshot(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_WRITE) {
+        return response;
+    }
+    match api.backends.remove_snapshot(params.get("name").unwrap_or("")) {
+        Ok(()) => Response::new(Status::NO_CONTENT),
+        Err(status) => backend_error(status),
+    }
+}
+
+fn acl_target(params: &Params) -> Option<(u32, &str)> {
+    let target = match params.get("kind")? {
+        "pools" => ACL_TARGET_POOL,
+        "volumes" => ACL_TARGET_VOLUME,
+        _ => return None,
+    };
+    Some((target, params.get("name")?))
+}
+
+fn rights_json(rights: u32) -> String {
+    array(ACL_RIGHTS.iter().filter(|(bit, _)| rights & bit != 0).map(|(_, name)| format!("\"{}\"", name)))
+}
+
+fn parse_rights(value: Option<&Value>) -> Option<u32> {
+    let Some(value) = value else {
+        return Some(0);
+    };
+    let mut rights = 0;
+    for name in value.as_array()? {
+        let name = name.as_str()?;
+        rights |= ACL_RIGHTS.iter().find(|(_, known)| *known == name)?.0;
+    }
+    Some(rights)
+}
+
+fn parse_entry(value: &Value) -> Option<AclEntryRecord> {
+    let (kind, id) = match value.get("principal")?.as_str()? {
+        "everyone" => (ACL_PRINCIPAL_EVERYONE, 0),
+        "capability" => (ACL_PRINCIPAL_CAPABILITY, value.get("id")?.as_u64()?),
+        "process" => (ACL_PRINCIPAL_PROCESS, value.get("id")?.as_u64()?),
+        _ => return None,
+    };
+    Some(AclEntryRecord { kind, id, allow: parse_rights(value.get("allow"))?, deny: parse_rights(value.get("deny"))? })
+}
+
+fn acl(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_ADMIN) {
+        return response;
+    }
+    let Some((target, name)) = acl_target(params) else {
+        return error(Status::NOT_FOUND, "not found");
+    };
+    let acl = match api.backends.acl(target, name) {
+        Ok(acl) => acl,
+        Err(status) => return backend_error(status),
+    };
+
+    let entries = acl.entries.iter().map(|entry| {
+        let principal = match entry.kind {
+            ACL_PRINCIPAL_CAPABILITY => "capability",
+            ACL_PRINCIPAL_PROCESS => "process",
+            _ => "everyone",
+        };
+        ObjectWriter::new()
+            .string("principal", principal)
+            .unsigned("id", entry.id)
+            .raw("allow", &rights_json(entry.allow))
+            .raw("deny", &rights_json(entry.deny))
+            .finish()
+    });
+    Response::json(
+        Status::OK,
+        ObjectWriter::new().unsigned("generation", acl.generation).raw("entries", &array(entries)).finish(),
+    )
+}
+
+fn set_acl(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_ADMIN) {
+        return response;
+    }
+    let Some((target, name)) = acl_target(params) else {
+        return error(Status::NOT_FOUND, "not found");
+    };
+    let body = match json_body(request) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    let Some(generation) = body.get("generation").and_then(Value::as_u64) else {
+        return error(Status::BAD_REQUEST, "expected the \"generation\" the entries were read at");
+    };
+    let entries: Option<Vec<AclEntryRecord>> =
+        body.get("entries").and_then(Value::as_array).map(|entries| entries.iter().map(parse_entry).collect());
+    let entries = match entries {
+        Some(Some(entries)) if entries.len() <= ACL_MAX_ENTRIES => entries,
+        _ => return error(Status::BAD_REQUEST, "\"entries\" must be a list of valid entries"),
+    };
+
+    match api.backends.set_acl(target, name, generation, &entries) {
+        Ok(generation) => Response::json(Status::OK, ObjectWriter::new().unsigned("generation", generation).finish()),
+        Err(status) => backend_error(status),
+    }
+}
+
+fn crashes(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_READ) {
+        return response;
+    }
+    let since = match request.query_param("since").map(str::parse::<u64>) {
+        None => 0,
+        Some(Ok(since)) => since,
+        Some(Err(_)) => return error(Status::BAD_REQUEST, "\"since\" must be a number"),
+    };
+    let crashes = match api.backends.crashes(request.query_param("component").unwrap_or(""), since) {
+        Ok(crashes) => crashes,
+        Err(status) => return backend_error(status),
+    };
+
+    let items = crashes.iter().map(|crash| {
+        ObjectWriter::new()
+            .unsigned("id", crash.id as u64)
+            .unsigned("time", crash.time)
+            .string("component", &crash.component)
+            .unsigned("pid", crash.pid)
+            .string("reason", &crash.reason)
+            .unsigned("size", crash.size)
+            .finish()
+    });
+    Response::json(Status::OK, ObjectWriter::new().raw("crashes", &array(items)).finish())
+}
+
+fn crash_id(params: &Params) -> Option<u32> {
+    params.get("id")?.parse().ok()
+}
+
+fn crash(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_READ) {
+        return response;
+    }
+    let Some(id) = crash_id(params) else {
+        return error(Status::NOT_FOUND, "not found");
+    };
+    match api.backends.crash_data(id) {
+        Ok(data) => Response::with_body(Status::OK, "application/octet-stream", data)
+            .header("Content-Disposition", &format!("attachment; filename=\"crash-{}.core\"", id)),
+        Err(status) => backend_error(status),
+    }
+}
+
+fn remove_crash(api: &mut Api, request: &Request, params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_STORAGE_WRITE) {
+        return response;
+    }
+    let Some(id) = crash_id(params) else {
+        return error(Status::NOT_FOUND, "not found");
+    };
+    match api.backends.remove_crash(id) {
+        Ok(()) => Response::new(Status::NO_CONTENT),
+        Err(status) => backend_error(status),
+    }
+}
+
+fn alerts(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_ALERTS_READ) {
+        return response;
+    }
+    let log = api.alerts.borrow();
+    let active = array(log.active().iter().map(|key| {
+        let mut json = String::new();
+        orion_http::json::escape_into(&mut json, key);
+        json
+    }));
+    let recent = array(log.since(0).map(|alert| alert.to_json()));
+    Response::json(Status::OK, ObjectWriter::new().raw("active", &active).raw("alerts", &recent).finish())
+}
+
+fn alert_stream(api: &mut Api, request: &Request, _params: &Params) -> Response {
+    if let Err(response) = authorize(api, request, SCOPE_ALERTS_READ) {
+        return response;
+    }
+    // Resume after the last event the client saw, or start with new alerts
+    let cursor = request
+        .header("Last-Event-ID")
+        .and_then(|id| id.trim().parse::<u64>().ok())
+        .map(|id| id + 1)
+        .unwrap_or_else(|| api.alerts.borrow().next_sequence());
+
+    Response::chunked(Status::OK, "text/event-stream", AlertStream::new(api.alerts.clone(), cursor))
+        .header("Cache-Control", "no-store")
+}
+
+/// Probe the backends and record condition changes in the alert log
+pub fn watch(api: &mut Api) {
+    let mut log = api.alerts.borrow_mut();
+
+    let io = api.backends.devices();
+    log.update("server:io:unavailable", io.is_err(), Severity::Critical, "I/O server is not answering");
+
+    let interfaces = api.backends.interfaces();
+    log.update("server:net:unavailable", interfaces.is_err(), Severity::Critical, "network server is not answering");
+    for iface in interfaces.iter().flatten() {
+        let key = format!("interface:{}:error", iface.name);
+        let message = format!("interface {} reports an error", iface.name);
+        log.update(&key, iface.state == IFACE_STATE_ERROR, Severity::Critical, &message);
+        let key = format!("interface:{}:down", iface.name);
+        let message = format!("interface {} is down", iface.name);
+        log.update(&key, iface.state == IFACE_STATE_DOWN, Severity::Warning, &message);
+    }
+
+    let pools = api.backends.pools();
+    log.update("server:storage:unavailable", pools.is_err(), Severity::Critical, "LVM driver is not answering");
+    for pool in pools.iter().flatten() {
+        let key = format!("pool:{}:failed", pool.name);
+        let message = format!("volume group {} failed", pool.name);
+        log.update(&key, pool.state == POOL_STATE_FAILED, Severity::Critical, &message);
+
+        for snapshot in api.backends.snapshots(&pool.name).iter().flatten() {
+            let key = format!("snapshot:{}:invalid", snapshot.name);
+            let message = format!("snapshot {} of {} is invalid", snapshot.name, snapshot.origin);
+            log.update(&key, snapshot.status == SNAPSHOT_STATUS_INVALID, Severity::Warning, &message);
+        }
+    }
+
+    // The thermal server is optional: no alert when it is missing
+    for zone in api.backends.thermal_zones().iter().flatten() {
+        let key = format!("thermal:{}:tripped", zone.name);
+        let message = format!("thermal zone {} is over a trip point, cooling engaged", zone.name);
+        let tripped = zone.level != THERMAL_LEVEL_NONE && zone.level < THERMAL_LEVEL_CRITICAL;
+        log.update(&key, tripped, Severity::Warning, &message);
+        let key = format!("thermal:{}:critical", zone.name);
+        let message = format!("thermal zone {} reached its critical temperature", zone.name);
+        log.update(&key, zone.level == THERMAL_LEVEL_CRITICAL, Severity::Critical, &message);
+    }
+}
@@ -0,0 +1,559 @@
+/*
+ * Orion Operating System - Management Backends
+ *
+ * Clients of the servers the management API fronts: the I/O server for
+ * the device and driver inventory, the network server for interfaces
+ * (services/net/iface_ipc.h), the LVM driver for storage pools and
+ * snapshots (its management control protocol, carried as
+ * LVM_IOCTL_CONTROL requests), the thermal server for the thermal
+ * zones and the crash dump server for stored core dumps. Every call is a
+ * request/reply exchange whose reply starts with an i32 status.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+use crate::protocol::{STATUS_EINVAL, STATUS_EIO, STATUS_OK};
+
+// I/O server (services/io/src/protocol.rs)
+const IO_OP_LIST_DEVICES: u32 = 8;
+
+// Network server interface requests
+const IFACE_OP_LIST: u32 = 16;
+const IFACE_OP_CONFIGURE: u32 = 17;
+const IFACE_SET_STATE: u32 = 1 << 0;
+const IFACE_SET_MTU: u32 = 1 << 1;
+const IFACE_RECORD_SIZE: usize = 120;
+const IFACE_NAME_SIZE: usize = 32;
+pub const IFACE_MIN_MTU: u32 = 68;
+pub const IFACE_MAX_MTU: u32 = 9216;
+
+// LVM control protocol (drivers/block/src/lvm.rs)
+const LVM_CTRL_LIST_POOLS: u32 = 1;
+const LVM_CTRL_LIST_SNAPSHOTS: u32 = 2;
+const LVM_CTRL_CREATE_SNAPSHOT: u32 = 3;
+const LVM_CTRL_REMOVE_SNAPSHOT: u32 = 4;
+const LVM_CTRL_GET_ACL: u32 = 9;
+const LVM_CTRL_SET_ACL: u32 = 10;
+
+// Thermal server (services/thermal/src/protocol.rs)
+const THERMAL_OP_LIST_ZONES: u32 = 5;
+const THERMAL_ZONE_RECORD_SIZE: usize = 36;
+const THERMAL_NAME_SIZE: usize = 16;
+
+// Crash dump server (services/crashd/src/protocol.rs)
+const CRASH_OP_LIST: u32 = 2;
+const CRASH_OP_READ: u32 = 3;
+const CRASH_OP_DELETE: u32 = 4;
+const CRASH_MAX_READ: u32 = 60 * 1024;
+/// Largest dump served over the API; bigger ones fail with EFBIG
+pub const CRASH_MAX_DOWNLOAD: u64 = 64 * 1024 * 1024;
+pub const STATUS_EFBIG: i32 = -27;
+
+/// Interface states reported by the network server
+pub const IFACE_STATE_DOWN: u32 = 0;
+pub const IFACE_STATE_UP: u32 = 1;
+pub const IFACE_STATE_RUNNING: u32 = 2;
+pub const IFACE_STATE_ERROR: u32 = 3;
+
+/// Volume group state of a failed pool
+pub const POOL_STATE_FAILED: u32 = 3;
+/// Snapshot status of an invalidated snapshot
+pub const SNAPSHOT_STATUS_INVALID: u32 = 2;
+
+/// What a storage ACL is attached to
+pub const ACL_TARGET_POOL: u32 = 0;
+pub const ACL_TARGET_VOLUME: u32 = 1;
+/// Principal kinds of an ACL entry
+pub const ACL_PRINCIPAL_EVERYONE: u32 = 0;
+pub const ACL_PRINCIPAL_CAPABILITY: u32 = 1;
+pub const ACL_PRINCIPAL_PROCESS: u32 = 2;
+/// Rights of an ACL entry, by name
+pub const ACL_RIGHTS: [(u32, &str); 4] = [(1 << 0, "read"), (1 << 1, "write"), (1 << 2, "snapshot"), (1 << 3, "admin")];
+/// Most entries the LVM driver accepts in one ACL
+pub const ACL_MAX_ENTRIES: usize = 64;
+
+/// Most severe tripped trip of a thermal zone: none, or 1 + the trip kind
+pub const THERMAL_LEVEL_NONE: u32 = 0;
+pub const THERMAL_LEVEL_CRITICAL: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceRecord {
+    pub handle: u64,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub driver_pid: Option<u64>,
+    pub driver: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceRecord {
+    pub name: String,
+    pub kind: u32,
+    pub state: u32,
+    pub mtu: u32,
+    pub flags: u32,
+    pub mac: [u8; 6],
+    /// rx/tx packets, rx/tx bytes, rx/tx errors, rx/tx dropped
+    pub counters: [u64; 8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolRecord {
+    pub name: String,
+    pub size: u64,
+    pub free: u64,
+    pub state: u32,
+    pub volumes: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotRecord {
+    pub name: String,
+    pub origin: String,
+    pub size: u64,
+    pub status: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AclEntryRecord {
+    pub kind: u32,
+    /// Capability or process id, 0 for everyone
+    pub id: u64,
+    pub allow: u32,
+    pub deny: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclRecord {
+    /// Passed back on update; the driver refuses it once the list changed
+    pub generation: u64,
+    pub entries: Vec<AclEntryRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThermalZoneRecord {
+    pub name: String,
+    /// Millidegrees Celsius, None while the zone has no fresh reading
+    pub temp_mc: Option<i32>,
+    pub level: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashRecord {
+    pub id: u32,
+    /// Realtime clock of the crash, in nanoseconds
+    pub time: u64,
+    /// Process that crashed, 0 for the kernel
+    pub pid: u64,
+    pub size: u64,
+    pub component: String,
+    pub reason: String,
+}
+
+/// Little-endian reader over a reply payload
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn done(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(self.bytes(8)?);
+        Some(u64::from_le_bytes(raw))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        Some(String::from(core::str::from_utf8(self.bytes(len)?).ok()?))
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Decode repeated records until the payload is exhausted
+fn decode_all<T>(payload: &[u8], mut record: impl FnMut(&mut Reader) -> Option<T>) -> Option<Vec<T>> {
+    let mut reader = Reader::new(payload);
+    let mut records = Vec::new();
+    while !reader.done() {
+        records.push(record(&mut reader)?);
+    }
+    Some(records)
+}
+
+pub fn decode_devices(payload: &[u8]) -> Option<Vec<DeviceRecord>> {
+    decode_all(payload, |reader| {
+        Some(DeviceRecord {
+            handle: reader.u64()?,
+            vendor_id: reader.u16()?,
+            device_id: reader.u16()?,
+            driver_pid: Some(reader.u64()?).filter(|pid| *pid != 0),
+            driver: reader.string()?,
+        })
+    })
+}
+
+pub fn decode_interfaces(payload: &[u8]) -> Option<Vec<InterfaceRecord>> {
+    if !payload.len().is_multiple_of(IFACE_RECORD_SIZE) {
+        return None;
+    }
+    decode_all(payload, |reader| {
+        let name = reader.bytes(IFACE_NAME_SIZE)?;
+        let name = &name[..name.iter().position(|byte| *byte == 0).unwrap_or(IFACE_NAME_SIZE)];
+        let mut record = InterfaceRecord {
+            name: String::from(core::str::from_utf8(name).ok()?),
+            kind: reader.u32()?,
+            state: reader.u32()?,
+            mtu: reader.u32()?,
+            flags: reader.u32()?,
+            mac: [0; 6],
+            counters: [0; 8],
+        };
+        record.mac.copy_from_slice(reader.bytes(6)?);
+        reader.bytes(2)?;
+        for counter in record.counters.iter_mut() {
+            *counter = reader.u64()?;
+        }
+        Some(record)
+    })
+}
+
+pub fn decode_pools(payload: &[u8]) -> Option<Vec<PoolRecord>> {
+    decode_all(payload, |reader| {
+        Some(PoolRecord {
+            name: reader.string()?,
+            size: reader.u64()?,
+            free: reader.u64()?,
+            state: reader.u32()?,
+            volumes: reader.u32()?,
+        })
+    })
+}
+
+pub fn decode_snapshots(payload: &[u8]) -> Option<Vec<SnapshotRecord>> {
+    decode_all(payload, |reader| {
+        Some(SnapshotRecord {
+            name: reader.string()?,
+            origin: reader.string()?,
+            size: reader.u64()?,
+            status: reader.u32()?,
+        })
+    })
+}
+
+pub fn decode_acl(payload: &[u8]) -> Option<AclRecord> {
+    let mut reader = Reader::new(payload);
+    let generation = reader.u64()?;
+    let count = reader.u32()? as usize;
+    let entries = decode_all(reader.bytes(payload.len() - 12)?, |reader| {
+        Some(AclEntryRecord { kind: reader.u32()?, id: reader.u64()?, allow: reader.u32()?, deny: reader.u32()? })
+    })?;
+    (entries.len() == count).then_some(AclRecord { generation, entries })
+}
+
+pub fn decode_thermal_zones(payload: &[u8]) -> Option<Vec<ThermalZoneRecord>> {
+    if !payload.len().is_multiple_of(THERMAL_ZONE_RECORD_SIZE) {
+        return None;
+    }
+    decode_all(payload, |reader| {
+        let name = reader.bytes(THERMAL_NAME_SIZE)?;
+        let name = &name[..name.iter().position(|byte| *byte == 0).unwrap_or(THERMAL_NAME_SIZE)];
+        let name = String::from(core::str::from_utf8(name).ok()?);
+        let _id = reader.u32()?;
+        let valid = reader.u32()? != 0;
+        let temp_mc = reader.u32()? as i32;
+        let level = reader.u32()?;
+        let _tripped = reader.u32()?;
+        Some(ThermalZoneRecord { name, temp_mc: Some(temp_mc).filter(|_| valid), level })
+    })
+}
+
+pub fn decode_crashes(payload: &[u8]) -> Option<Vec<CrashRecord>> {
+    decode_all(payload, |reader| {
+        Some(CrashRecord {
+            id: reader.u32()?,
+            time: reader.u64()?,
+            pid: reader.u64()?,
+            size: reader.u64()?,
+            component: reader.string()?,
+            reason: reader.string()?,
+        })
+    })
+}
+
+/// Requested interface changes; None leaves a setting alone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceChange {
+    pub up: Option<bool>,
+    pub mtu: Option<u32>,
+}
+
+pub struct Backends {
+    io: IpcChannel,
+    net: IpcChannel,
+    storage: IpcChannel,
+    thermal: IpcChannel,
+    crash: IpcChannel,
+}
+
+impl Backends {
+    pub fn connect() -> Self {
+        Self {
+            io: IpcChannel::connect("io"),
+            net: IpcChannel::connect("net"),
+            storage: IpcChannel::connect("lvm-advanced"),
+            thermal: IpcChannel::connect("thermal"),
+            crash: IpcChannel::connect("crashd"),
+        }
+    }
+
+    /// One request; Ok with the payload or Err with the failing status
+    fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = channel.call(request).map_err(|_| STATUS_EIO)?;
+        if response.len() < 4 {
+            return Err(STATUS_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    pub fn devices(&mut self) -> Result<Vec<DeviceRecord>, i32> {
+        let payload = Self::call(&mut self.io, &IO_OP_LIST_DEVICES.to_le_bytes())?;
+        decode_devices(&payload).ok_or(STATUS_EIO)
+    }
+
+    pub fn interfaces(&mut self) -> Result<Vec<InterfaceRecord>, i32> {
+        let payload = Self::call(&mut self.net, &IFACE_OP_LIST.to_le_bytes())?;
+        decode_interfaces(&payload).ok_or(STATUS_EIO)
+    }
+
+    pub fn configure_interface(&mut self, name: &str, change: InterfaceChange) -> Result<(), i32> {
+        if name.is_empty() || name.len() >= IFACE_NAME_SIZE {
+            return Err(STATUS_EINVAL);
+        }
+        let mut mask = 0;
+        if change.up.is_some() {
+            mask |= IFACE_SET_STATE;
+        }
+        if change.mtu.is_some() {
+            mask |= IFACE_SET_MTU;
+        }
+        let state = if change.up == Some(true) { IFACE_STATE_UP } else { IFACE_STATE_DOWN };
+
+        let mut request = Vec::with_capacity(4 + IFACE_NAME_SIZE + 12);
+        request.extend_from_slice(&IFACE_OP_CONFIGURE.to_le_bytes());
+        request.extend_from_slice(name.as_bytes());
+        request.resize(4 + IFACE_NAME_SIZE, 0);
+        request.extend_from_slice(&mask.to_le_bytes());
+        request.extend_from_slice(&state.to_le_bytes());
+        request.extend_from_slice(&change.mtu.unwrap_or(0).to_le_bytes());
+        Self::call(&mut self.net, &request).map(|_| ())
+    }
+
+    pub fn pools(&mut self) -> Result<Vec<PoolRecord>, i32> {
+        let payload = Self::call(&mut self.storage, &LVM_CTRL_LIST_POOLS.to_le_bytes())?;
+        decode_pools(&payload).ok_or(STATUS_EIO)
+    }
+
+    pub fn snapshots(&mut self, pool: &str) -> Result<Vec<SnapshotRecord>, i32> {
+        let mut request = LVM_CTRL_LIST_SNAPSHOTS.to_le_bytes().to_vec();
+        put_string(&mut request, pool);
+        let payload = Self::call(&mut self.storage, &request)?;
+        decode_snapshots(&payload).ok_or(STATUS_EIO)
+    }
+
+    pub fn create_snapshot(&mut self, name: &str, origin: &str, size: u64) -> Result<(), i32> {
+        let mut request = LVM_CTRL_CREATE_SNAPSHOT.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        put_string(&mut request, origin);
+        request.extend_from_slice(&size.to_le_bytes());
+        Self::call(&mut self.storage, &request).map(|_| ())
+    }
+
+    pub fn remove_snapshot(&mut self, name: &str) -> Result<(), i32> {
+        let mut request = LVM_CTRL_REMOVE_SNAPSHOT.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        Self::call(&mut self.storage, &request).map(|_| ())
+    }
+
+    pub fn acl(&mut self, target: u32, name: &str) -> Result<AclRecord, i32> {
+        let mut request = LVM_CTRL_GET_ACL.to_le_bytes().to_vec();
+        request.extend_from_slice(&target.to_le_bytes());
+        put_string(&mut request, name);
+        let payload = Self::call(&mut self.storage, &request)?;
+        decode_acl(&payload).ok_or(STATUS_EIO)
+    }
+
+    /// Replace the entries of an ACL read at `generation`; returns the new generation
+    pub fn set_acl(&mut self, target: u32, name: &str, generation: u64, entries: &[AclEntryRecord]) -> Result<u64, i32> {
+        let mut request = LVM_CTRL_SET_ACL.to_le_bytes().to_vec();
+        request.extend_from_slice(&target.to_le_bytes());
+        put_string(&mut request, name);
+        request.extend_from_slice(&generation.to_le_bytes());
+        request.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for entry in entries {
+            request.extend_from_slice(&entry.kind.to_le_bytes());
+            request.extend_from_slice(&entry.id.to_le_bytes());
+            request.extend_from_slice(&entry.allow.to_le_bytes());
+            request.extend_from_slice(&entry.deny.to_le_bytes());
+        }
+        let payload = Self::call(&mut self.storage, &request)?;
+        Reader::new(&payload).u64().ok_or(STATUS_EIO)
+    }
+
+    pub fn thermal_zones(&mut self) -> Result<Vec<ThermalZoneRecord>, i32> {
+        let payload = Self::call(&mut self.thermal, &THERMAL_OP_LIST_ZONES.to_le_bytes())?;
+        decode_thermal_zones(&payload).ok_or(STATUS_EIO)
+    }
+
+    /// Stored dumps of `component`, or of every component, from `since` on
+    pub fn crashes(&mut self, component: &str, since: u64) -> Result<Vec<CrashRecord>, i32> {
+        let mut request = CRASH_OP_LIST.to_le_bytes().to_vec();
+        request.extend_from_slice(&since.to_le_bytes());
+        put_string(&mut request, component);
+        let payload = Self::call(&mut self.crash, &request)?;
+        decode_crashes(&payload).ok_or(STATUS_EIO)
+    }
+
+    /// Contents of dump `id`
+    pub fn crash_data(&mut self, id: u32) -> Result<Vec<u8>, i32> {
+        let mut data = Vec::new();
+        loop {
+            let mut request = CRASH_OP_READ.to_le_bytes().to_vec();
+            request.extend_from_slice(&id.to_le_bytes());
+            request.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            request.extend_from_slice(&CRASH_MAX_READ.to_le_bytes());
+            let piece = Self::call(&mut self.crash, &request)?;
+            if piece.is_empty() {
+                return Ok(data);
+            }
+            if (data.len() + piece.len()) as u64 > CRASH_MAX_DOWNLOAD {
+                return Err(STATUS_EFBIG);
+            }
+            data.extend_from_slice(&piece);
+        }
+    }
+
+    pub fn remove_crash(&mut self, id: u32) -> Result<(), i32> {
+        let mut request = CRASH_OP_DELETE.to_le_bytes().to_vec();
+        request.extend_from_slice(&id.to_le_bytes());
+        Self::call(&mut self.crash, &request).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_records() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&0x42u64.to_le_bytes());
+        payload.extend_from_slice(&0x8086u16.to_le_bytes());
+        payload.extend_from_slice(&0x100eu16.to_le_bytes());
+        payload.extend_from_slice(&0u64.to_le_bytes());
+        put_string(&mut payload, "");
+        let devices = decode_devices(&payload).unwrap();
+        assert_eq!(devices[0].vendor_id, 0x8086);
+        assert_eq!(devices[0].driver_pid, None);
+        assert!(decode_devices(&payload[..payload.len() - 1]).is_none());
+
+        let mut record = [0u8; IFACE_RECORD_SIZE];
+        record[..4].copy_from_slice(b"eth0");
+        record[36..40].copy_from_slice(&IFACE_STATE_UP.to_le_bytes());
+        record[40..44].copy_from_slice(&1500u32.to_le_bytes());
+        record[48..54].copy_from_slice(&[0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+        record[112..120].copy_from_slice(&7u64.to_le_bytes());
+        let interfaces = decode_interfaces(&record).unwrap();
+        assert_eq!(interfaces[0].name, "eth0");
+        assert_eq!(interfaces[0].mtu, 1500);
+        assert_eq!(interfaces[0].mac[5], 0x56);
+        assert_eq!(interfaces[0].counters[7], 7);
+        assert!(decode_interfaces(&record[..100]).is_none());
+
+        let mut payload = Vec::new();
+        put_string(&mut payload, "snap");
+        put_string(&mut payload, "root");
+        payload.extend_from_slice(&(1u64 << 28).to_le_bytes());
+        payload.extend_from_slice(&SNAPSHOT_STATUS_INVALID.to_le_bytes());
+        let snapshots = decode_snapshots(&payload).unwrap();
+        assert_eq!(snapshots[0].origin, "root");
+        assert_eq!(snapshots[0].status, SNAPSHOT_STATUS_INVALID);
+        assert_eq!(decode_pools(&[]), Some(Vec::new()));
+
+        let mut record = [0u8; THERMAL_ZONE_RECORD_SIZE];
+        record[..3].copy_from_slice(b"cpu");
+        record[20..24].copy_from_slice(&1u32.to_le_bytes());
+        record[24..28].copy_from_slice(&(-5_000i32).to_le_bytes());
+        record[28..32].copy_from_slice(&THERMAL_LEVEL_CRITICAL.to_le_bytes());
+        let zones = decode_thermal_zones(&record).unwrap();
+        assert_eq!(zones[0].name, "cpu");
+        assert_eq!(zones[0].temp_mc, Some(-5_000));
+        assert_eq!(zones[0].level, THERMAL_LEVEL_CRITICAL);
+        record[20] = 0;
+        assert_eq!(decode_thermal_zones(&record).unwrap()[0].temp_mc, None);
+        assert!(decode_thermal_zones(&record[..30]).is_none());
+
+        let mut payload = 3u64.to_le_bytes().to_vec();
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&ACL_PRINCIPAL_CAPABILITY.to_le_bytes());
+        payload.extend_from_slice(&0xa0u64.to_le_bytes());
+        payload.extend_from_slice(&0xfu32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        let acl = decode_acl(&payload).unwrap();
+        assert_eq!(acl.generation, 3);
+        assert_eq!(acl.entries[0], AclEntryRecord { kind: ACL_PRINCIPAL_CAPABILITY, id: 0xa0, allow: 0xf, deny: 0 });
+        // The count must match the entries
+        payload[8] = 2;
+        assert!(decode_acl(&payload).is_none());
+        assert!(decode_acl(&payload[..10]).is_none());
+
+        let mut payload = 4u32.to_le_bytes().to_vec();
+        payload.extend_from_slice(&1_700_000_000_000_000_000u64.to_le_bytes());
+        payload.extend_from_slice(&0u64.to_le_bytes());
+        payload.extend_from_slice(&5_000u64.to_le_bytes());
+        put_string(&mut payload, "kernel");
+        put_string(&mut payload, "Guard page hit");
+        let crashes = decode_crashes(&payload).unwrap();
+        assert_eq!(crashes[0].id, 4);
+        assert_eq!(crashes[0].component, "kernel");
+        assert_eq!(crashes[0].reason, "Guard page hit");
+        assert!(decode_crashes(&payload[..payload.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,233 @@
+/*
+ * Orion Operating System - Management Server
+ *
+ * Remote management daemon: a REST/JSON API (see api.rs) over orion_http
+ * to query the driver inventory, configure network interfaces, manage
+ * storage pools, snapshots and their access lists, and stream alerts.
+ * Callers authenticate with bearer tokens carrying capability-like
+ * scopes; tokens are issued and revoked by local administrators over IPC
+ * (see protocol.rs).
+ *
+ * The HTTP listener is a TLS socket of the network server, polled between
+ * IPC checks. Backends are probed periodically to raise alerts.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use orion_cap::Capability;
+use orion_http::socket::{NetChannel, NetListener};
+use orion_http::{Router, Server, ServerConfig};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod alerts;
+mod api;
+mod backends;
+mod protocol;
+mod tokens;
+
+use alerts::AlertLog;
+use api::Api;
+use backends::Backends;
+use protocol::*;
+use tokens::{encode_secret, IssueError, TokenStore, TOKEN_SECRET_SIZE};
+
+/// Port of the HTTPS listener
+const HTTPS_PORT: u16 = 8443;
+const HTTPS_BACKLOG: u16 = 16;
+
+/// Pause between two polls of the HTTP connections
+const POLL_INTERVAL_NS: u64 = 10_000_000;
+
+/// Polls between two probes of the backends (about five seconds)
+const WATCH_INTERVAL: u64 = 500;
+
+/// Entropy GET_RANDOM request opcode (see services/entropy/src/protocol.rs)
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+
+/// IPC channel to the network server used by the HTTP listener
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+struct MgmtServer {
+    http: Option<Server<NetListener<NetIpc>>>,
+    router: Router<Api>,
+    api: Api,
+    entropy: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+    polls: u64,
+}
+
+impl MgmtServer {
+    fn new() -> Self {
+        Self {
+            http: None,
+            router: api::router(),
+            api: Api {
+                tokens: TokenStore::new(),
+                alerts: Rc::new(RefCell::new(AlertLog::new())),
+                backends: Backends::connect(),
+                denied: 0,
+            },
+            entropy: IpcChannel::connect("entropy"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+            polls: 0,
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+
+            if self.polls % WATCH_INTERVAL == 0 {
+                api::watch(&mut self.api);
+            }
+            self.polls += 1;
+
+            if let Some(http) = self.http.as_mut() {
+                http.poll(&self.router, &mut self.api);
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match MgmtRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            MgmtRequest::Status => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let status = match request {
+            MgmtRequest::IssueToken { scopes, name } => self.issue_token(scopes, &name, &mut payload),
+            MgmtRequest::RevokeToken { id } => {
+                if self.api.tokens.revoke(id) {
+                    STATUS_OK
+                } else {
+                    STATUS_ENOENT
+                }
+            }
+            MgmtRequest::ListTokens => {
+                for token in self.api.tokens.iter() {
+                    encode_token(token.id, token.scopes, &token.name, &mut payload);
+                }
+                STATUS_OK
+            }
+            MgmtRequest::Status => {
+                let stats = self.http.as_ref().map(|http| http.stats()).unwrap_or_default();
+                payload.extend_from_slice(&(self.api.tokens.iter().count() as u32).to_le_bytes());
+                payload.extend_from_slice(&(self.api.alerts.borrow().active().len() as u32).to_le_bytes());
+                payload.extend_from_slice(&stats.requests.to_le_bytes());
+                payload.extend_from_slice(&self.api.denied.to_le_bytes());
+                STATUS_OK
+            }
+            MgmtRequest::EnableTls { certificate_handle, key_handle } => self.listen(certificate_handle, key_handle),
+        };
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+
+    fn issue_token(&mut self, scopes: u32, name: &str, out: &mut Vec<u8>) -> i32 {
+        let secret = match self.random_secret() {
+            Some(secret) => secret,
+            None => return STATUS_EIO,
+        };
+        match self.api.tokens.issue(name, scopes, &secret) {
+            Ok(id) => {
+                out.extend_from_slice(&id.to_le_bytes());
+                out.extend_from_slice(encode_secret(&secret).as_bytes());
+                STATUS_OK
+            }
+            Err(IssueError::Full) => STATUS_ENOSPC,
+            Err(IssueError::InvalidName | IssueError::InvalidScopes) => STATUS_EINVAL,
+        }
+    }
+
+    /// Draw a token secret from the entropy service
+    fn random_secret(&mut self) -> Option<[u8; TOKEN_SECRET_SIZE]> {
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+        request.extend_from_slice(&(TOKEN_SECRET_SIZE as u32).to_le_bytes());
+        request.extend_from_slice(&0u32.to_le_bytes());
+
+        let response = self.entropy.call(&request).ok()?;
+        if response.len() < 4 + TOKEN_SECRET_SIZE || response[..4] != STATUS_OK.to_le_bytes() {
+            return None;
+        }
+        let mut secret = [0u8; TOKEN_SECRET_SIZE];
+        secret.copy_from_slice(&response[4..4 + TOKEN_SECRET_SIZE]);
+        Some(secret)
+    }
+
+    /// Open the HTTPS listener with keyring-held credentials
+    fn listen(&mut self, certificate_handle: u64, key_handle: u64) -> i32 {
+        if self.http.is_some() {
+            return STATUS_EBUSY;
+        }
+        let listener = match NetListener::bind(NetIpc(IpcChannel::connect("net")), 0, HTTPS_PORT, HTTPS_BACKLOG) {
+            Ok(listener) => listener,
+            Err(status) => return status,
+        };
+        // Dropping the listener closes the socket if TLS cannot be set up
+        if let Err(status) = listener.enable_tls(certificate_handle, key_handle) {
+            return status;
+        }
+        self.http = Some(Server::new(listener, ServerConfig::default()));
+        STATUS_OK
+    }
+}
+
+fn main() {
+    let mut server = MgmtServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * Orion Operating System - Management Server Protocol
+ *
+ * Administrative IPC requests of the management server, used to hand out
+ * and revoke API tokens. All fields are little-endian; every message
+ * starts with a 32-bit opcode and every reply starts with a 32-bit signed
+ * status (0 or a negative errno).
+ *
+ *   ISSUE_TOKEN   scopes:u32 name       -> id:u32 token (64 hex digits)
+ *   REVOKE_TOKEN  id:u32                -> (empty)
+ *   LIST_TOKENS   (none)                -> records: id:u32 scopes:u32 name
+ *   STATUS        (none)                -> tokens:u32 active_alerts:u32
+ *                                          requests:u64 denied:u64
+ *   ENABLE_TLS    cert:u64 key:u64      -> (empty)
+ *
+ * The API is only served over TLS: its listener opens once ENABLE_TLS has
+ * supplied the keyring handles of the server certificate and key.
+ *
+ * Strings are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_ISSUE_TOKEN: u32 = 1;
+pub const OP_REVOKE_TOKEN: u32 = 2;
+pub const OP_LIST_TOKENS: u32 = 3;
+pub const OP_STATUS: u32 = 4;
+pub const OP_ENABLE_TLS: u32 = 5;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MgmtRequest {
+    IssueToken { scopes: u32, name: String },
+    RevokeToken { id: u32 },
+    ListTokens,
+    Status,
+    EnableTls { certificate_handle: u64, key_handle: u64 },
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, utf-8 bytes` field
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some(String::from(core::str::from_utf8(bytes).ok()?))
+}
+
+impl MgmtRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_ISSUE_TOKEN => Some(MgmtRequest::IssueToken {
+                scopes: read_u32(data, 4)?,
+                name: read_string(data, 8)?,
+            }),
+            OP_REVOKE_TOKEN => Some(MgmtRequest::RevokeToken { id: read_u32(data, 4)? }),
+            OP_LIST_TOKENS => Some(MgmtRequest::ListTokens),
+            OP_STATUS => Some(MgmtRequest::Status),
+            OP_ENABLE_TLS => Some(MgmtRequest::EnableTls {
+                certificate_handle: read_u64(data, 4)?,
+                key_handle: read_u64(data, 12)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// LIST_TOKENS record
+pub fn encode_token(id: u32, scopes: u32, name: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&id.to_le_bytes());
+    out.extend_from_slice(&scopes.to_le_bytes());
+    out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    out.extend_from_slice(name.as_bytes());
+}
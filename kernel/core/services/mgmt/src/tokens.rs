@@ -0,0 +1,234 @@
+/*
+ * Orion Operating System - Management API Tokens
+ *
+ * Bearer tokens of the management API. Each token carries a set of scopes
+ * limiting the endpoints it may call. Only the SHA-512 digest of a token
+ * is kept: the secret itself is handed out once, when it is issued, and
+ * presented tokens are compared against every digest in constant time.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::sha512::{Sha512, SHA512_DIGEST_SIZE};
+
+// Scopes
+pub const SCOPE_INVENTORY_READ: u32 = 1 << 0;
+pub const SCOPE_NETWORK_READ: u32 = 1 << 1;
+pub const SCOPE_NETWORK_WRITE: u32 = 1 << 2;
+pub const SCOPE_STORAGE_READ: u32 = 1 << 3;
+pub const SCOPE_STORAGE_WRITE: u32 = 1 << 4;
+pub const SCOPE_ALERTS_READ: u32 = 1 << 5;
+pub const SCOPE_STORAGE_ADMIN: u32 = 1 << 6;
+pub const SCOPE_ALL: u32 = (1 << 7) - 1;
+
+/// Length of a token secret; tokens travel hex encoded
+pub const TOKEN_SECRET_SIZE: usize = 32;
+
+pub const MAX_TOKENS: usize = 64;
+pub const MAX_TOKEN_NAME: usize = 64;
+
+/// Scope names as used in token listings
+pub const SCOPE_NAMES: [(u32, &str); 7] = [
+    (SCOPE_INVENTORY_READ, "inventory:read"),
+    (SCOPE_NETWORK_READ, "network:read"),
+    (SCOPE_NETWORK_WRITE, "network:write"),
+    (SCOPE_STORAGE_READ, "storage:read"),
+    (SCOPE_STORAGE_WRITE, "storage:write"),
+    (SCOPE_ALERTS_READ, "alerts:read"),
+    (SCOPE_STORAGE_ADMIN, "storage:admin"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// No usable bearer token: answered with 401
+    Unauthenticated,
+    /// Valid token lacking the scope: answered with 403
+    Forbidden,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueError {
+    Full,
+    InvalidName,
+    InvalidScopes,
+}
+
+pub struct Token {
+    pub id: u32,
+    pub name: String,
+    pub scopes: u32,
+    digest: [u8; SHA512_DIGEST_SIZE],
+}
+
+pub struct TokenStore {
+    tokens: Vec<Token>,
+    next_id: u32,
+}
+
+/// Compare two digests without exiting on the first difference
+fn digest_eq(a: &[u8; SHA512_DIGEST_SIZE], b: &[u8; SHA512_DIGEST_SIZE]) -> bool {
+    let mut difference = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        difference |= x ^ y;
+    }
+    difference == 0
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Secret of an `Authorization: Bearer <hex>` header value
+fn bearer_secret(header: &str) -> Option<[u8; TOKEN_SECRET_SIZE]> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim().as_bytes();
+    if token.len() != TOKEN_SECRET_SIZE * 2 {
+        return None;
+    }
+
+    let mut secret = [0u8; TOKEN_SECRET_SIZE];
+    for (byte, pair) in secret.iter_mut().zip(token.chunks(2)) {
+        *byte = (hex_value(pair[0])? << 4) | hex_value(pair[1])?;
+    }
+    Some(secret)
+}
+
+/// Hex form of a secret, as presented by clients
+pub fn encode_secret(secret: &[u8; TOKEN_SECRET_SIZE]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut text = String::with_capacity(TOKEN_SECRET_SIZE * 2);
+    for byte in secret.iter() {
+        text.push(DIGITS[(byte >> 4) as usize] as char);
+        text.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    text
+}
+
+impl TokenStore {
+    pub fn new() -> Self {
+        Self {
+            tokens: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Register `secret` (drawn from the entropy service by the caller)
+    /// and return the id of the new token
+    pub fn issue(&mut self, name: &str, scopes: u32, secret: &[u8; TOKEN_SECRET_SIZE]) -> Result<u32, IssueError> {
+        if scopes == 0 || scopes & !SCOPE_ALL != 0 {
+            return Err(IssueError::InvalidScopes);
+        }
+        if name.is_empty() || name.len() > MAX_TOKEN_NAME || name.chars().any(|c| c.is_control()) {
+            return Err(IssueError::InvalidName);
+        }
+        if self.tokens.len() >= MAX_TOKENS {
+            return Err(IssueError::Full);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tokens.push(Token {
+            id,
+            name: String::from(name),
+            scopes,
+            digest: Sha512::digest(secret),
+        });
+        Ok(id)
+    }
+
+    pub fn revoke(&mut self, id: u32) -> bool {
+        let count = self.tokens.len();
+        self.tokens.retain(|token| token.id != id);
+        self.tokens.len() != count
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Token> {
+        self.tokens.iter()
+    }
+
+    /// Check an Authorization header value against `scope`; returns the
+    /// id of the matching token
+    pub fn authorize(&self, header: Option<&str>, scope: u32) -> Result<u32, AuthError> {
+        let secret = header.and_then(bearer_secret).ok_or(AuthError::Unauthenticated)?;
+        let digest = Sha512::digest(&secret);
+
+        // Visit every token so that timing does not reveal which one matched
+        let mut matched = None;
+        for token in self.tokens.iter() {
+            if digest_eq(&token.digest, &digest) {
+                matched = Some(token);
+            }
+        }
+
+        match matched {
+            Some(token) if token.scopes & scope == scope => Ok(token.id),
+            Some(_) => Err(AuthError::Forbidden),
+            None => Err(AuthError::Unauthenticated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn test_authorizes_by_scope() {
+        let mut store = TokenStore::new();
+        let reader = [7u8; TOKEN_SECRET_SIZE];
+        let admin = [9u8; TOKEN_SECRET_SIZE];
+        let reader_id = store.issue("dashboard", SCOPE_INVENTORY_READ | SCOPE_ALERTS_READ, &reader).unwrap();
+        let admin_id = store.issue("ops", SCOPE_ALL, &admin).unwrap();
+
+        let header = format!("Bearer {}", encode_secret(&reader));
+        assert_eq!(store.authorize(Some(&header), SCOPE_INVENTORY_READ), Ok(reader_id));
+        assert_eq!(store.authorize(Some(&header), SCOPE_NETWORK_WRITE), Err(AuthError::Forbidden));
+        let header = format!("bearer {}", encode_secret(&admin).to_uppercase());
+        assert_eq!(store.authorize(Some(&header), SCOPE_STORAGE_WRITE), Ok(admin_id));
+
+        assert_eq!(store.authorize(None, SCOPE_INVENTORY_READ), Err(AuthError::Unauthenticated));
+        let unknown = format!("Bearer {}", encode_secret(&[1u8; TOKEN_SECRET_SIZE]));
+        assert_eq!(store.authorize(Some(&unknown), SCOPE_INVENTORY_READ), Err(AuthError::Unauthenticated));
+        let basic = format!("Basic {}", encode_secret(&reader));
+        assert_eq!(store.authorize(Some(&basic), SCOPE_INVENTORY_READ), Err(AuthError::Unauthenticated));
+        assert_eq!(store.authorize(Some("Bearer 0707"), SCOPE_INVENTORY_READ), Err(AuthError::Unauthenticated));
+
+        // Revoked tokens stop working immediately
+        assert!(store.revoke(reader_id));
+        assert!(!store.revoke(reader_id));
+        let header = format!("Bearer {}", encode_secret(&reader));
+        assert_eq!(store.authorize(Some(&header), SCOPE_INVENTORY_READ), Err(AuthError::Unauthenticated));
+    }
+
+    #[test]
+    fn test_validates_issue_requests() {
+        let mut store = TokenStore::new();
+        let secret = [3u8; TOKEN_SECRET_SIZE];
+        assert_eq!(store.issue("x", 0, &secret), Err(IssueError::InvalidScopes));
+        assert_eq!(store.issue("x", 1 << 6, &secret), Err(IssueError::InvalidScopes));
+        assert_eq!(store.issue("", SCOPE_ALL, &secret), Err(IssueError::InvalidName));
+        assert_eq!(store.issue("a\nb", SCOPE_ALL, &secret), Err(IssueError::InvalidName));
+
+        for _ in 0..MAX_TOKENS {
+            store.issue("bulk", SCOPE_ALERTS_READ, &secret).unwrap();
+        }
+        assert_eq!(store.issue("one-more", SCOPE_ALERTS_READ, &secret), Err(IssueError::Full));
+        assert!(store.iter().all(|token| token.name == "bulk"));
+    }
+}
@@ -0,0 +1,268 @@
+/*
+ * Orion Operating System - NBD Exports
+ *
+ * What a client can attach to: a logical volume of the LVM driver or a
+ * file of the VFS, behind a common Backend. Volumes are read and written
+ * through the volume requests of the LVM control protocol, files through
+ * the direct file requests of the file system server; both move at most
+ * MAX_TRANSFER bytes per IPC call, so larger NBD requests are split.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+use crate::protocol::{STATUS_EINVAL, STATUS_EIO, STATUS_OK};
+
+/// Largest chunk moved by one IPC call, for volumes and files alike
+const MAX_TRANSFER: usize = 64 * 1024;
+
+// LVM control requests (see drivers/block/src/lvm.rs)
+const LVM_CTRL_VOLUME_INFO: u32 = 5;
+const LVM_CTRL_VOLUME_READ: u32 = 6;
+const LVM_CTRL_VOLUME_WRITE: u32 = 7;
+const LVM_CTRL_VOLUME_FLUSH: u32 = 8;
+
+// File system direct file requests (see services/fs/src/files.rs)
+const FS_OP_OPEN: u32 = 0x41;
+const FS_OP_READ_AT: u32 = 0x42;
+const FS_OP_WRITE_AT: u32 = 0x43;
+const FS_OP_SYNC: u32 = 0x44;
+const FS_OP_CLOSE: u32 = 0x45;
+const FS_OPEN_READ: u32 = 0o1;
+const FS_OPEN_WRITE: u32 = 0o2;
+
+// Export kinds
+pub const KIND_VOLUME: u32 = 1;
+pub const KIND_FILE: u32 = 2;
+
+/// Storage behind an export. Offsets and lengths given to read and write
+/// are multiples of block_size and stay below size; errors are negative
+/// statuses.
+pub trait Backend {
+    fn size(&self) -> u64;
+    fn block_size(&self) -> u32;
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32>;
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32>;
+    fn flush(&mut self) -> Result<(), i32>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportStats {
+    pub reads: u64,
+    pub writes: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub errors: u64,
+}
+
+pub struct Export {
+    pub id: u32,
+    pub name: String,
+    /// Volume name or file path
+    pub source: String,
+    pub kind: u32,
+    pub read_only: bool,
+    pub backend: Box<dyn Backend>,
+    /// Capability and process the export was added with; clients only
+    /// reach it while that capability still holds the rights it needed
+    pub capability: u64,
+    pub owner: u64,
+    pub stats: ExportStats,
+}
+
+/// Issue a request and split the reply
+fn call(channel: &mut IpcChannel, request: &[u8]) -> Result<Vec<u8>, i32> {
+    let response = channel.call(request).map_err(|_| STATUS_EIO)?;
+    if response.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+        STATUS_OK => Ok(response[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Logical volume of the LVM driver
+pub struct VolumeBackend {
+    channel: IpcChannel,
+    name: String,
+    size: u64,
+    block_size: u32,
+}
+
+impl VolumeBackend {
+    pub fn open(mut channel: IpcChannel, name: &str) -> Result<Self, i32> {
+        let mut request = LVM_CTRL_VOLUME_INFO.to_le_bytes().to_vec();
+        put_string(&mut request, name);
+        let payload = call(&mut channel, &request)?;
+        let size = read_u64(&payload, 0).ok_or(STATUS_EIO)?;
+        let block_size = read_u32(&payload, 8).filter(|size| size.is_power_of_two()).ok_or(STATUS_EIO)?;
+        Ok(Self { channel, name: String::from(name), size, block_size })
+    }
+
+    fn request(&self, opcode: u32, offset: u64) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        put_string(&mut request, &self.name);
+        request.extend_from_slice(&offset.to_le_bytes());
+        request
+    }
+}
+
+impl Backend for VolumeBackend {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        let mut position = offset;
+        for chunk in buffer.chunks_mut(MAX_TRANSFER) {
+            let mut request = self.request(LVM_CTRL_VOLUME_READ, position);
+            request.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            let data = call(&mut self.channel, &request)?;
+            if data.len() != chunk.len() {
+                return Err(STATUS_EIO);
+            }
+            chunk.copy_from_slice(&data);
+            position += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32> {
+        let mut position = offset;
+        for chunk in data.chunks(MAX_TRANSFER) {
+            let mut request = self.request(LVM_CTRL_VOLUME_WRITE, position);
+            request.extend_from_slice(chunk);
+            call(&mut self.channel, &request)?;
+            position += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), i32> {
+        let mut request = LVM_CTRL_VOLUME_FLUSH.to_le_bytes().to_vec();
+        put_string(&mut request, &self.name);
+        call(&mut self.channel, &request).map(|_| ())
+    }
+}
+
+/// Regular file of the VFS, served byte-addressed
+pub struct FileBackend {
+    channel: IpcChannel,
+    handle: u32,
+    size: u64,
+}
+
+impl FileBackend {
+    pub fn open(mut channel: IpcChannel, path: &str, read_only: bool) -> Result<Self, i32> {
+        let flags = if read_only { FS_OPEN_READ } else { FS_OPEN_READ | FS_OPEN_WRITE };
+        let mut request = FS_OP_OPEN.to_le_bytes().to_vec();
+        request.extend_from_slice(&flags.to_le_bytes());
+        put_string(&mut request, path);
+        let payload = call(&mut channel, &request)?;
+        let handle = read_u32(&payload, 0).ok_or(STATUS_EIO)?;
+        let size = read_u64(&payload, 4).ok_or(STATUS_EIO)?;
+        Ok(Self { channel, handle, size })
+    }
+
+    fn request(&self, opcode: u32) -> Vec<u8> {
+        let mut request = opcode.to_le_bytes().to_vec();
+        request.extend_from_slice(&self.handle.to_le_bytes());
+        request
+    }
+}
+
+impl Backend for FileBackend {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn block_size(&self) -> u32 {
+        1
+    }
+
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        let mut position = offset;
+        for chunk in buffer.chunks_mut(MAX_TRANSFER) {
+            let mut request = self.request(FS_OP_READ_AT);
+            request.extend_from_slice(&position.to_le_bytes());
+            request.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            let data = call(&mut self.channel, &request)?;
+            // The export never outgrows the size it was opened with, but
+            // the file may have shrunk since: the missing tail reads as zeros
+            if data.len() > chunk.len() {
+                return Err(STATUS_EIO);
+            }
+            chunk[..data.len()].copy_from_slice(&data);
+            chunk[data.len()..].fill(0);
+            position += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32> {
+        let mut position = offset;
+        for chunk in data.chunks(MAX_TRANSFER) {
+            let mut request = self.request(FS_OP_WRITE_AT);
+            request.extend_from_slice(&position.to_le_bytes());
+            request.extend_from_slice(chunk);
+            let written = call(&mut self.channel, &request).map(|payload| read_u32(&payload, 0))?;
+            if written != Some(chunk.len() as u32) {
+                return Err(STATUS_EIO);
+            }
+            position += chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), i32> {
+        let request = self.request(FS_OP_SYNC);
+        call(&mut self.channel, &request).map(|_| ())
+    }
+}
+
+impl Drop for FileBackend {
+    fn drop(&mut self) {
+        let request = self.request(FS_OP_CLOSE);
+        let _ = call(&mut self.channel, &request);
+    }
+}
+
+/// Open the backend of an export of `kind`
+pub fn open_backend(kind: u32, source: &str, read_only: bool) -> Result<Box<dyn Backend>, i32> {
+    match kind {
+        KIND_VOLUME => Ok(Box::new(VolumeBackend::open(IpcChannel::connect("lvm-advanced"), source)?)),
+        KIND_FILE => Ok(Box::new(FileBackend::open(IpcChannel::connect("fs"), source, read_only)?)),
+        _ => Err(STATUS_EINVAL),
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * Orion Operating System - NBD Server
+ *
+ * Network Block Device server exporting logical volumes and VFS files to
+ * other machines (see session.rs for the protocol, export.rs for the
+ * backends): Orion hosts replicate volumes to each other through it, and
+ * any OS with an NBD client can mount Orion storage. TLS is terminated
+ * by the network server once a client asks for STARTTLS.
+ *
+ * Exports are added by an administrator, who hands over the capability
+ * clients are served under: the capability needs CAP_READ, and CAP_WRITE
+ * unless the export is read-only, and it is checked again every time a
+ * client lists or selects exports, so revoking it withdraws the export.
+ * While the server runs, each export is advertised over mDNS as a
+ * _nbd._tcp instance.
+ *
+ * The server is idle until START (see protocol.rs); the listener and the
+ * sessions are then polled between IPC checks.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_http::socket::{NetChannel, NetListener, NetStream};
+use orion_http::{IoError, Listener};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_mdns::client::MDNS_SERVICE_NAME;
+use orion_mdns::{Discovery, MdnsChannel};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the exports and the transfer buffers
+const HEAP_SIZE: usize = 4 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod export;
+mod protocol;
+mod session;
+
+use export::{open_backend, Export, ExportStats, KIND_FILE, KIND_VOLUME};
+use protocol::*;
+use session::{NbdTransport, Security, Session, SessionStats};
+
+/// Pause between two polls of the sessions
+const POLL_INTERVAL_NS: u64 = 1_000_000;
+
+const LISTEN_BACKLOG: u16 = 8;
+const MAX_SESSIONS: usize = 32;
+const MAX_EXPORTS: usize = 64;
+
+/// DNS-SD service type of the exports
+const MDNS_SERVICE_TYPE: &str = "_nbd._tcp";
+
+/// IPC channel to the network server used by the listener
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+impl NbdTransport for NetStream<NetIpc> {
+    fn start_tls(&mut self, certificate_handle: u64, key_handle: u64) -> Result<(), i32> {
+        NetStream::start_tls(self, certificate_handle, key_handle)
+    }
+}
+
+/// IPC channel to the mDNS responder
+struct MdnsIpc(IpcChannel);
+
+impl MdnsChannel for MdnsIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Rights a client is served an export under
+fn export_rights(read_only: bool) -> u64 {
+    if read_only {
+        CAP_READ
+    } else {
+        CAP_READ | CAP_WRITE
+    }
+}
+
+/// Whether the capability an export was added with still grants it
+fn authorized(capabilities: &Capability, export: &Export) -> bool {
+    capabilities.check_rights(export.capability, export_rights(export.read_only), export.owner)
+}
+
+struct Running {
+    listener: NetListener<NetIpc>,
+    port: u16,
+    security: Security,
+}
+
+struct NbdServer {
+    running: Option<Running>,
+    exports: Vec<Export>,
+    next_export_id: u32,
+    sessions: Vec<Session<NetStream<NetIpc>>>,
+    /// Counters of sessions already closed
+    closed: SessionStats,
+    discovery: Discovery<MdnsIpc>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl NbdServer {
+    fn new() -> Self {
+        Self {
+            running: None,
+            exports: Vec::new(),
+            next_export_id: 1,
+            sessions: Vec::new(),
+            closed: SessionStats::default(),
+            discovery: Discovery::new(MdnsIpc(IpcChannel::connect(MDNS_SERVICE_NAME))),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            if self.running.is_some() {
+                self.poll_sessions();
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    /// Advertise an export; discovery is a convenience, so failures are
+    /// ignored
+    fn announce(&mut self, name: &str, port: u16) {
+        let txt = format!("export={}", name);
+        let _ = self.discovery.register(name, MDNS_SERVICE_TYPE, port, &[txt.as_str()]);
+    }
+
+    fn withdraw(&mut self, name: &str) {
+        let _ = self.discovery.unregister(name, MDNS_SERVICE_TYPE);
+    }
+
+    fn start(&mut self, config: StartConfig) -> i32 {
+        if self.running.is_some() {
+            return STATUS_EBUSY;
+        }
+        let tls = config.flags & FLAG_TLS != 0;
+        let required = config.flags & FLAG_TLS_REQUIRED != 0;
+        if required && !tls {
+            return STATUS_EINVAL;
+        }
+        let listener = match NetListener::bind(NetIpc(IpcChannel::connect("net")), 0, config.port, LISTEN_BACKLOG) {
+            Ok(listener) => listener,
+            Err(status) => return status,
+        };
+        let security = Security { tls: tls.then_some((config.certificate_handle, config.key_handle)), required };
+        self.running = Some(Running { listener, port: config.port, security });
+
+        let names: Vec<String> = self.exports.iter().map(|export| export.name.clone()).collect();
+        for name in names {
+            self.announce(&name, config.port);
+        }
+        STATUS_OK
+    }
+
+    fn stop(&mut self) -> i32 {
+        if self.running.take().is_none() {
+            return STATUS_ENOENT;
+        }
+        for mut session in self.sessions.drain(..) {
+            session.close();
+            add_stats(&mut self.closed, &session.stats);
+        }
+        let names: Vec<String> = self.exports.iter().map(|export| export.name.clone()).collect();
+        for name in names {
+            self.withdraw(&name);
+        }
+        STATUS_OK
+    }
+
+    fn add_export(
+        &mut self,
+        kind: u32,
+        flags: u32,
+        name: String,
+        source: String,
+        message: &IpcMessage,
+    ) -> Result<u32, i32> {
+        if kind != KIND_VOLUME && kind != KIND_FILE {
+            return Err(STATUS_EINVAL);
+        }
+        if self.exports.iter().any(|export| export.name == name) {
+            return Err(STATUS_EEXIST);
+        }
+        if self.exports.len() >= MAX_EXPORTS {
+            return Err(STATUS_EBUSY);
+        }
+        // Clients are served with the rights of the capability handed over
+        let read_only = flags & EXPORT_READ_ONLY != 0;
+        if !self.capabilities.check_rights(message.capability, export_rights(read_only), message.sender) {
+            return Err(STATUS_EPERM);
+        }
+        let backend = open_backend(kind, &source, read_only)?;
+
+        let id = self.next_export_id;
+        self.next_export_id += 1;
+        if let Some(port) = self.running.as_ref().map(|running| running.port) {
+            self.announce(&name, port);
+        }
+        self.exports.push(Export {
+            id,
+            name,
+            source,
+            kind,
+            read_only,
+            backend,
+            capability: message.capability,
+            owner: message.sender,
+            stats: ExportStats::default(),
+        });
+        Ok(id)
+    }
+
+    fn remove_export(&mut self, name: &str) -> i32 {
+        let Some(index) = self.exports.iter().position(|export| export.name == name) else {
+            return STATUS_ENOENT;
+        };
+        // Sessions on it fail their next request with ESHUTDOWN
+        let mut export = self.exports.remove(index);
+        let _ = export.backend.flush();
+        if self.running.is_some() {
+            self.withdraw(name);
+        }
+        STATUS_OK
+    }
+
+    fn poll_sessions(&mut self) {
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+        loop {
+            match running.listener.accept() {
+                Ok(stream) if self.sessions.len() < MAX_SESSIONS => {
+                    self.sessions.push(Session::new(stream, running.security));
+                }
+                // Dropping the stream closes the connection
+                Ok(_) => {}
+                Err(IoError::WouldBlock) | Err(IoError::Closed) => break,
+            }
+        }
+
+        let capabilities = &self.capabilities;
+        for session in self.sessions.iter_mut() {
+            session.poll(&mut self.exports, &mut |export: &Export| authorized(capabilities, export));
+        }
+        let closed = &mut self.closed;
+        self.sessions.retain(|session| {
+            if session.is_closed() {
+                add_stats(closed, &session.stats);
+            }
+            !session.is_closed()
+        });
+    }
+
+    fn list_exports(&self) -> Vec<u8> {
+        let mut payload = (self.exports.len() as u32).to_le_bytes().to_vec();
+        for export in self.exports.iter() {
+            let clients = self.sessions.iter().filter(|session| session.export() == Some(export.id)).count();
+            let flags = if export.read_only { EXPORT_READ_ONLY } else { 0 };
+            for value in [export.id, export.kind, flags] {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+            payload.extend_from_slice(&export.backend.size().to_le_bytes());
+            payload.extend_from_slice(&(clients as u32).to_le_bytes());
+            for value in [export.stats.reads, export.stats.writes, export.stats.errors] {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+            put_string(&mut payload, &export.name);
+            put_string(&mut payload, &export.source);
+        }
+        payload
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match NbdRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            NbdRequest::ListExports | NbdRequest::Status => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let status = match request {
+            NbdRequest::Start(config) => self.start(config),
+            NbdRequest::Stop => self.stop(),
+            NbdRequest::AddExport { kind, flags, name, source } => {
+                match self.add_export(kind, flags, name, source, &message) {
+                    Ok(id) => {
+                        payload.extend_from_slice(&id.to_le_bytes());
+                        STATUS_OK
+                    }
+                    Err(status) => status,
+                }
+            }
+            NbdRequest::RemoveExport { name } => self.remove_export(&name),
+            NbdRequest::ListExports => {
+                payload = self.list_exports();
+                STATUS_OK
+            }
+            NbdRequest::Status => {
+                let mut totals = self.closed;
+                for session in self.sessions.iter() {
+                    add_stats(&mut totals, &session.stats);
+                }
+                for value in [self.exports.len() as u32, self.sessions.len() as u32] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                for value in [totals.bytes_read, totals.bytes_written] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                STATUS_OK
+            }
+        };
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+fn add_stats(total: &mut SessionStats, stats: &SessionStats) {
+    total.requests += stats.requests;
+    total.bytes_read += stats.bytes_read;
+    total.bytes_written += stats.bytes_written;
+    total.errors += stats.errors;
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = NbdServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * Orion Operating System - NBD Server Protocol
+ *
+ * Administrative IPC requests of the NBD server. All fields are
+ * little-endian; every message starts with a 32-bit opcode and every
+ * reply starts with a 32-bit signed status (0 or a negative errno).
+ *
+ *   START          port:u16 flags:u16 cert:u64 key:u64 -> (empty)
+ *   STOP           (none)                              -> (empty)
+ *   ADD_EXPORT     kind:u32 flags:u32 name source      -> id:u32
+ *   REMOVE_EXPORT  name                                -> (empty)
+ *   LIST_EXPORTS   (none)                              -> count:u32 records
+ *   STATUS         (none)                              -> exports:u32
+ *                                                         sessions:u32
+ *                                                         bytes_read:u64
+ *                                                         bytes_written:u64
+ *
+ * START listens on `port` (0 for 10809). cert and key are keyring handles
+ * offered through STARTTLS when FLAG_TLS is set; FLAG_TLS_REQUIRED also
+ * refuses clients that do not upgrade. Exports are a logical volume
+ * (KIND_VOLUME, `source` is the volume name) or a file (KIND_FILE,
+ * `source` is its path), read-only with EXPORT_READ_ONLY, and can be
+ * added before the server is started.
+ *
+ * LIST_EXPORTS records are id:u32 kind:u32 flags:u32 size:u64
+ * clients:u32 reads:u64 writes:u64 errors:u64 name source. Strings are a
+ * `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_START: u32 = 1;
+pub const OP_STOP: u32 = 2;
+pub const OP_ADD_EXPORT: u32 = 3;
+pub const OP_REMOVE_EXPORT: u32 = 4;
+pub const OP_LIST_EXPORTS: u32 = 5;
+pub const OP_STATUS: u32 = 6;
+
+// START flags
+pub const FLAG_TLS: u16 = 1 << 0;
+pub const FLAG_TLS_REQUIRED: u16 = 1 << 1;
+
+// ADD_EXPORT flags
+pub const EXPORT_READ_ONLY: u32 = 1 << 0;
+
+/// IANA port of NBD
+pub const DEFAULT_PORT: u16 = 10809;
+
+/// Longest export name, as NBD clients accept it
+pub const MAX_EXPORT_NAME: usize = 256;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EEXIST: i32 = -17;
+pub const STATUS_EINVAL: i32 = -22;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StartConfig {
+    pub port: u16,
+    pub flags: u16,
+    pub certificate_handle: u64,
+    pub key_handle: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NbdRequest {
+    Start(StartConfig),
+    Stop,
+    AddExport { kind: u32, flags: u32, name: String, source: String },
+    RemoveExport { name: String },
+    ListExports,
+    Status,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, utf-8 bytes` field
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some(String::from(core::str::from_utf8(bytes).ok()?))
+}
+
+pub fn put_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+impl NbdRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_START => {
+                let port = match read_u16(data, 4)? {
+                    0 => DEFAULT_PORT,
+                    port => port,
+                };
+                Some(NbdRequest::Start(StartConfig {
+                    port,
+                    flags: read_u16(data, 6)?,
+                    certificate_handle: read_u64(data, 8)?,
+                    key_handle: read_u64(data, 16)?,
+                }))
+            }
+            OP_STOP => Some(NbdRequest::Stop),
+            OP_ADD_EXPORT => {
+                let name = read_string(data, 12)?;
+                let source = read_string(data, 16 + name.len())?;
+                if name.is_empty() || name.len() > MAX_EXPORT_NAME || source.is_empty() {
+                    return None;
+                }
+                Some(NbdRequest::AddExport { kind: read_u32(data, 4)?, flags: read_u32(data, 8)?, name, source })
+            }
+            OP_REMOVE_EXPORT => Some(NbdRequest::RemoveExport { name: read_string(data, 4)? }),
+            OP_LIST_EXPORTS => Some(NbdRequest::ListExports),
+            OP_STATUS => Some(NbdRequest::Status),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&OP_START.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&FLAG_TLS.to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&8u64.to_le_bytes());
+        let expected = StartConfig { port: DEFAULT_PORT, flags: FLAG_TLS, certificate_handle: 7, key_handle: 8 };
+        assert_eq!(NbdRequest::decode(&data), Some(NbdRequest::Start(expected)));
+        assert_eq!(NbdRequest::decode(&data[..23]), None);
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&OP_ADD_EXPORT.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&EXPORT_READ_ONLY.to_le_bytes());
+        put_string(&mut data, "iso");
+        put_string(&mut data, "/images/install.iso");
+        let expected = NbdRequest::AddExport {
+            kind: 2,
+            flags: EXPORT_READ_ONLY,
+            name: String::from("iso"),
+            source: String::from("/images/install.iso"),
+        };
+        assert_eq!(NbdRequest::decode(&data), Some(expected));
+
+        let mut data = OP_ADD_EXPORT.to_le_bytes().to_vec();
+        data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        put_string(&mut data, "");
+        put_string(&mut data, "root");
+        assert_eq!(NbdRequest::decode(&data), None);
+    }
+}
@@ -0,0 +1,815 @@
+/*
+ * Orion Operating System - NBD Sessions
+ *
+ * One client connection speaking the fixed newstyle NBD protocol: the
+ * handshake, option haggling (EXPORT_NAME, ABORT, LIST, STARTTLS, INFO
+ * and GO), then simple replies to READ, WRITE, WRITE_ZEROES, FLUSH and
+ * DISC. STARTTLS upgrades the stream in the network server once its
+ * acknowledgement is out; when TLS is required nothing but STARTTLS and
+ * ABORT is answered before it.
+ *
+ * A client only sees and selects exports the authorize callback accepts,
+ * which the server uses to check again the capability each export was
+ * added with. Sessions name their export by id, so an export removed
+ * under a session fails its requests with ESHUTDOWN instead of reaching
+ * another export.
+ *
+ * Sessions are polled like RFB sessions: input is buffered until a whole
+ * option or request is there, and no new request is handled while more
+ * than OUTPUT_HIGH_WATER bytes of replies wait for the client.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_http::{IoError, Transport};
+
+use crate::export::Export;
+use crate::protocol::{STATUS_EINVAL, STATUS_EPERM};
+
+const NBD_MAGIC: u64 = 0x4e42_444d_4147_4943;
+const NBD_IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+const NBD_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const NBD_REQUEST_MAGIC: u32 = 0x2560_9513;
+const NBD_SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+// Handshake flags, sent by the server and echoed by the client
+const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const FLAG_NO_ZEROES: u16 = 1 << 1;
+
+// Options
+pub const OPT_EXPORT_NAME: u32 = 1;
+pub const OPT_ABORT: u32 = 2;
+pub const OPT_LIST: u32 = 3;
+pub const OPT_STARTTLS: u32 = 5;
+pub const OPT_INFO: u32 = 6;
+pub const OPT_GO: u32 = 7;
+
+// Option reply types
+pub const REP_ACK: u32 = 1;
+pub const REP_SERVER: u32 = 2;
+pub const REP_INFO: u32 = 3;
+const REP_ERROR: u32 = 1 << 31;
+pub const REP_ERR_UNSUP: u32 = REP_ERROR | 1;
+pub const REP_ERR_POLICY: u32 = REP_ERROR | 2;
+pub const REP_ERR_INVALID: u32 = REP_ERROR | 3;
+pub const REP_ERR_TLS_REQD: u32 = REP_ERROR | 5;
+pub const REP_ERR_UNKNOWN: u32 = REP_ERROR | 6;
+
+// Information types of INFO and GO
+pub const INFO_EXPORT: u16 = 0;
+pub const INFO_BLOCK_SIZE: u16 = 3;
+
+// Transmission flags
+pub const TX_HAS_FLAGS: u16 = 1 << 0;
+pub const TX_READ_ONLY: u16 = 1 << 1;
+pub const TX_SEND_FLUSH: u16 = 1 << 2;
+pub const TX_SEND_FUA: u16 = 1 << 3;
+pub const TX_SEND_WRITE_ZEROES: u16 = 1 << 6;
+
+// Commands and command flags
+pub const CMD_READ: u16 = 0;
+pub const CMD_WRITE: u16 = 1;
+pub const CMD_DISC: u16 = 2;
+pub const CMD_FLUSH: u16 = 3;
+pub const CMD_WRITE_ZEROES: u16 = 6;
+pub const CMD_FLAG_FUA: u16 = 1 << 0;
+
+// Errors of simple replies (Linux errno values, as the protocol defines)
+pub const NBD_EPERM: u32 = 1;
+pub const NBD_EIO: u32 = 5;
+pub const NBD_EINVAL: u32 = 22;
+pub const NBD_ENOSPC: u32 = 28;
+pub const NBD_ESHUTDOWN: u32 = 108;
+
+const OPTION_HEADER_SIZE: usize = 16;
+const REQUEST_HEADER_SIZE: usize = 28;
+
+/// Longest option payload accepted
+const MAX_OPTION: usize = 4096;
+
+/// Largest READ or WRITE, advertised as the maximum block size
+pub const MAX_REQUEST: u32 = 1024 * 1024;
+
+/// Preferred block size advertised to clients
+const PREFERRED_BLOCK_SIZE: u32 = 4096;
+
+/// Queued output above which no new request is handled
+pub const OUTPUT_HIGH_WATER: usize = 2 * MAX_REQUEST as usize;
+
+/// Buffered input above which the client is dropped
+const MAX_INBOUND: usize = MAX_REQUEST as usize + 64 * 1024;
+
+/// Bytes requested from the transport per read
+const READ_SIZE: usize = 4096;
+
+/// Zeros written per backend call for WRITE_ZEROES
+const ZERO_CHUNK: usize = 64 * 1024;
+
+/// How clients may protect the connection
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Security {
+    /// Keyring handles of the certificate and key offered with STARTTLS
+    pub tls: Option<(u64, u64)>,
+    /// Refuse everything but STARTTLS and ABORT until TLS is up
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    ClientFlags,
+    Options,
+    Transmission,
+    /// Waiting for queued output to drain before closing
+    Closing,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionStats {
+    pub requests: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub errors: u64,
+}
+
+/// Transport able to switch to TLS mid-stream
+pub trait NbdTransport: Transport {
+    fn start_tls(&mut self, certificate_handle: u64, key_handle: u64) -> Result<(), i32>;
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_be_bytes(raw))
+}
+
+/// Simple reply error for a backend status
+fn errno(status: i32) -> u32 {
+    match status {
+        STATUS_EPERM => NBD_EPERM,
+        STATUS_EINVAL => NBD_EINVAL,
+        -28 => NBD_ENOSPC,
+        _ => NBD_EIO,
+    }
+}
+
+/// Request header of the transmission phase
+struct Request {
+    flags: u16,
+    command: u16,
+    cookie: u64,
+    offset: u64,
+    length: u32,
+}
+
+pub struct Session<T: NbdTransport> {
+    transport: T,
+    security: Security,
+    state: State,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+    /// Start TLS once the STARTTLS acknowledgement has been written
+    tls_pending: bool,
+    tls_active: bool,
+    no_zeroes: bool,
+    /// Id of the export selected for transmission
+    export: Option<u32>,
+    pub stats: SessionStats,
+}
+
+impl<T: NbdTransport> Session<T> {
+    pub fn new(transport: T, security: Security) -> Self {
+        let mut session = Self {
+            transport,
+            security,
+            state: State::ClientFlags,
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+            tls_pending: false,
+            tls_active: false,
+            no_zeroes: false,
+            export: None,
+            stats: SessionStats::default(),
+        };
+        session.outbound.extend_from_slice(&NBD_MAGIC.to_be_bytes());
+        session.outbound.extend_from_slice(&NBD_IHAVEOPT.to_be_bytes());
+        session.outbound.extend_from_slice(&(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).to_be_bytes());
+        session
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    /// Export the session transmits on, once selected
+    pub fn export(&self) -> Option<u32> {
+        self.export
+    }
+
+    pub fn close(&mut self) {
+        self.state = State::Closed;
+        self.transport.close();
+    }
+
+    /// Read, handle and answer what the client sent
+    pub fn poll(&mut self, exports: &mut [Export], authorize: &mut dyn FnMut(&Export) -> bool) {
+        if self.state == State::Closed {
+            return;
+        }
+        self.receive();
+        while !matches!(self.state, State::Closed | State::Closing) && !self.tls_pending {
+            if self.outbound.len() >= OUTPUT_HIGH_WATER {
+                break;
+            }
+            match self.handle(exports, authorize) {
+                Some(0) | None => break,
+                Some(consumed) => {
+                    self.inbound.drain(..consumed);
+                }
+            }
+        }
+        self.flush();
+    }
+
+    fn receive(&mut self) {
+        let mut buffer = [0u8; READ_SIZE];
+        while self.state != State::Closed && !self.tls_pending {
+            match self.transport.read(&mut buffer) {
+                Ok(0) | Err(IoError::Closed) => {
+                    self.close();
+                    return;
+                }
+                Ok(read) => {
+                    self.inbound.extend_from_slice(&buffer[..read]);
+                    if self.inbound.len() > MAX_INBOUND {
+                        self.close();
+                        return;
+                    }
+                }
+                Err(IoError::WouldBlock) => return,
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.outbound.is_empty() && self.state != State::Closed {
+            match self.transport.write(&self.outbound) {
+                Ok(written) => {
+                    self.outbound.drain(..written);
+                }
+                Err(IoError::WouldBlock) => return,
+                Err(IoError::Closed) => {
+                    self.close();
+                    return;
+                }
+            }
+        }
+        if self.state == State::Closing {
+            self.close();
+        } else if self.tls_pending && self.outbound.is_empty() {
+            self.tls_pending = false;
+            let (certificate_handle, key_handle) = self.security.tls.unwrap_or_default();
+            if self.transport.start_tls(certificate_handle, key_handle).is_err() {
+                self.close();
+            } else {
+                self.tls_active = true;
+                // Plaintext sent ahead of the TLS handshake is not trusted
+                self.inbound.clear();
+            }
+        }
+    }
+
+    fn option_reply(&mut self, option: u32, reply_type: u32, data: &[u8]) {
+        self.outbound.extend_from_slice(&NBD_REPLY_MAGIC.to_be_bytes());
+        self.outbound.extend_from_slice(&option.to_be_bytes());
+        self.outbound.extend_from_slice(&reply_type.to_be_bytes());
+        self.outbound.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        self.outbound.extend_from_slice(data);
+    }
+
+    fn simple_reply(&mut self, cookie: u64, error: u32, data: &[u8]) {
+        self.outbound.extend_from_slice(&NBD_SIMPLE_REPLY_MAGIC.to_be_bytes());
+        self.outbound.extend_from_slice(&error.to_be_bytes());
+        self.outbound.extend_from_slice(&cookie.to_be_bytes());
+        self.outbound.extend_from_slice(data);
+        if error != 0 {
+            self.stats.errors += 1;
+        }
+    }
+
+    /// Handle the option or request at the head of `inbound`; bytes
+    /// consumed, 0 or None when it is incomplete
+    fn handle(&mut self, exports: &mut [Export], authorize: &mut dyn FnMut(&Export) -> bool) -> Option<usize> {
+        match self.state {
+            State::ClientFlags => {
+                let flags = read_u32(&self.inbound, 0)?;
+                // Clients have to speak fixed newstyle and nothing we do not know
+                if flags & FLAG_FIXED_NEWSTYLE as u32 == 0
+                    || flags & !((FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES) as u32) != 0
+                {
+                    self.close();
+                    return None;
+                }
+                self.no_zeroes = flags & FLAG_NO_ZEROES as u32 != 0;
+                self.state = State::Options;
+                Some(4)
+            }
+            State::Options => {
+                let header = self.inbound.get(..OPTION_HEADER_SIZE)?;
+                let (magic, option) = (read_u64(header, 0)?, read_u32(header, 8)?);
+                let length = read_u32(header, 12)? as usize;
+                if magic != NBD_IHAVEOPT || length > MAX_OPTION {
+                    self.close();
+                    return None;
+                }
+                let data = self.inbound.get(OPTION_HEADER_SIZE..OPTION_HEADER_SIZE + length)?.to_vec();
+                self.handle_option(option, &data, exports, authorize);
+                Some(OPTION_HEADER_SIZE + length)
+            }
+            State::Transmission => {
+                let header = self.inbound.get(..REQUEST_HEADER_SIZE)?;
+                let request = Request {
+                    flags: read_u16(header, 4)?,
+                    command: read_u16(header, 6)?,
+                    cookie: read_u64(header, 8)?,
+                    offset: read_u64(header, 16)?,
+                    length: read_u32(header, 24)?,
+                };
+                if read_u32(header, 0)? != NBD_REQUEST_MAGIC {
+                    self.close();
+                    return None;
+                }
+                if request.command != CMD_WRITE {
+                    self.handle_request(&request, &[], exports);
+                    return Some(REQUEST_HEADER_SIZE);
+                }
+                // The payload of an oversized write cannot be skipped reliably
+                if request.length > MAX_REQUEST {
+                    self.close();
+                    return None;
+                }
+                let end = REQUEST_HEADER_SIZE + request.length as usize;
+                let data = self.inbound.get(REQUEST_HEADER_SIZE..end)?.to_vec();
+                self.handle_request(&request, &data, exports);
+                Some(end)
+            }
+            State::Closing | State::Closed => None,
+        }
+    }
+
+    fn handle_option(
+        &mut self,
+        option: u32,
+        data: &[u8],
+        exports: &mut [Export],
+        authorize: &mut dyn FnMut(&Export) -> bool,
+    ) {
+        if self.security.required && !self.tls_active && option != OPT_STARTTLS && option != OPT_ABORT {
+            if option == OPT_EXPORT_NAME {
+                // EXPORT_NAME has no way to report an error
+                self.close();
+            } else {
+                self.option_reply(option, REP_ERR_TLS_REQD, &[]);
+            }
+            return;
+        }
+
+        match option {
+            OPT_EXPORT_NAME => {
+                let index = core::str::from_utf8(data)
+                    .ok()
+                    .and_then(|name| exports.iter().position(|export| export.name == name))
+                    .filter(|&index| authorize(&exports[index]));
+                let Some(index) = index else {
+                    self.close();
+                    return;
+                };
+                let export = &exports[index];
+                self.outbound.extend_from_slice(&export.backend.size().to_be_bytes());
+                self.outbound.extend_from_slice(&transmission_flags(export).to_be_bytes());
+                if !self.no_zeroes {
+                    self.outbound.extend_from_slice(&[0; 124]);
+                }
+                self.export = Some(export.id);
+                self.state = State::Transmission;
+            }
+            OPT_ABORT => {
+                self.option_reply(option, REP_ACK, &[]);
+                self.state = State::Closing;
+            }
+            OPT_LIST => {
+                if !data.is_empty() {
+                    self.option_reply(option, REP_ERR_INVALID, &[]);
+                    return;
+                }
+                for export in exports.iter().filter(|export| authorize(export)) {
+                    let mut entry = (export.name.len() as u32).to_be_bytes().to_vec();
+                    entry.extend_from_slice(export.name.as_bytes());
+                    self.option_reply(option, REP_SERVER, &entry);
+                }
+                self.option_reply(option, REP_ACK, &[]);
+            }
+            OPT_STARTTLS => {
+                if self.security.tls.is_none() {
+                    self.option_reply(option, REP_ERR_UNSUP, &[]);
+                } else if self.tls_active || !data.is_empty() {
+                    self.option_reply(option, REP_ERR_INVALID, &[]);
+                } else {
+                    self.option_reply(option, REP_ACK, &[]);
+                    self.tls_pending = true;
+                }
+            }
+            OPT_INFO | OPT_GO => self.info(option, data, exports, authorize),
+            _ => self.option_reply(option, REP_ERR_UNSUP, &[]),
+        }
+    }
+
+    /// INFO and GO: describe an export, and for GO start transmitting on it
+    fn info(&mut self, option: u32, data: &[u8], exports: &mut [Export], authorize: &mut dyn FnMut(&Export) -> bool) {
+        let name_length = read_u32(data, 0).map(|length| length as usize);
+        let name = name_length.and_then(|length| data.get(4..4 + length));
+        let requests = name_length.and_then(|length| read_u16(data, 4 + length).map(|count| (length, count)));
+        let (name, well_formed) = match (name, requests) {
+            (Some(name), Some((length, count))) => (name, data.len() == 6 + length + 2 * count as usize),
+            _ => (&[][..], false),
+        };
+        if !well_formed {
+            self.option_reply(option, REP_ERR_INVALID, &[]);
+            return;
+        }
+
+        let Some(index) = exports.iter().position(|export| export.name.as_bytes() == name) else {
+            self.option_reply(option, REP_ERR_UNKNOWN, &[]);
+            return;
+        };
+        if !authorize(&exports[index]) {
+            self.option_reply(option, REP_ERR_POLICY, &[]);
+            return;
+        }
+
+        let export = &exports[index];
+        let mut info = INFO_EXPORT.to_be_bytes().to_vec();
+        info.extend_from_slice(&export.backend.size().to_be_bytes());
+        info.extend_from_slice(&transmission_flags(export).to_be_bytes());
+        self.option_reply(option, REP_INFO, &info);
+
+        // Always sent: volumes only take whole blocks
+        let minimum = export.backend.block_size();
+        let mut sizes = INFO_BLOCK_SIZE.to_be_bytes().to_vec();
+        for size in [minimum, PREFERRED_BLOCK_SIZE.max(minimum), MAX_REQUEST] {
+            sizes.extend_from_slice(&size.to_be_bytes());
+        }
+        self.option_reply(option, REP_INFO, &sizes);
+        self.option_reply(option, REP_ACK, &[]);
+
+        if option == OPT_GO {
+            self.export = Some(export.id);
+            self.state = State::Transmission;
+        }
+    }
+
+    fn handle_request(&mut self, request: &Request, data: &[u8], exports: &mut [Export]) {
+        self.stats.requests += 1;
+        if request.command == CMD_DISC {
+            self.state = State::Closing;
+            return;
+        }
+        let Some(export) = exports.iter_mut().find(|export| Some(export.id) == self.export) else {
+            self.simple_reply(request.cookie, NBD_ESHUTDOWN, &[]);
+            self.state = State::Closing;
+            return;
+        };
+
+        let result = match request.command {
+            CMD_READ => check_range(export, request, false).and_then(|_| {
+                let mut buffer = vec![0u8; request.length as usize];
+                export.backend.read(request.offset, &mut buffer).map_err(errno)?;
+                export.stats.reads += 1;
+                export.stats.bytes_read += buffer.len() as u64;
+                self.stats.bytes_read += buffer.len() as u64;
+                Ok(buffer)
+            }),
+            CMD_WRITE | CMD_WRITE_ZEROES => check_range(export, request, true).and_then(|_| {
+                if request.command == CMD_WRITE {
+                    export.backend.write(request.offset, data).map_err(errno)?;
+                } else {
+                    let zeros = vec![0u8; ZERO_CHUNK.min(request.length as usize)];
+                    let end = request.offset + request.length as u64;
+                    let mut offset = request.offset;
+                    while offset < end {
+                        let length = zeros.len().min((end - offset) as usize);
+                        export.backend.write(offset, &zeros[..length]).map_err(errno)?;
+                        offset += length as u64;
+                    }
+                }
+                if request.flags & CMD_FLAG_FUA != 0 {
+                    export.backend.flush().map_err(errno)?;
+                }
+                export.stats.writes += 1;
+                export.stats.bytes_written += request.length as u64;
+                self.stats.bytes_written += request.length as u64;
+                Ok(Vec::new())
+            }),
+            CMD_FLUSH => export.backend.flush().map(|_| Vec::new()).map_err(errno),
+            _ => Err(NBD_EINVAL),
+        };
+
+        match result {
+            Ok(payload) => self.simple_reply(request.cookie, 0, &payload),
+            Err(error) => {
+                export.stats.errors += 1;
+                self.simple_reply(request.cookie, error, &[]);
+            }
+        }
+    }
+}
+
+fn transmission_flags(export: &Export) -> u16 {
+    let mut flags = TX_HAS_FLAGS | TX_SEND_FLUSH | TX_SEND_FUA | TX_SEND_WRITE_ZEROES;
+    if export.read_only {
+        flags |= TX_READ_ONLY;
+    }
+    flags
+}
+
+/// Validate the range of a READ, WRITE or WRITE_ZEROES against the export
+fn check_range(export: &Export, request: &Request, write: bool) -> Result<(), u32> {
+    if write && export.read_only {
+        return Err(NBD_EPERM);
+    }
+    let block_size = export.backend.block_size() as u64;
+    let length = request.length as u64;
+    // WRITE_ZEROES carries no payload, so only reads and writes are bounded
+    let bounded = request.command == CMD_WRITE_ZEROES || request.length <= MAX_REQUEST;
+    if length == 0 || !bounded || !request.offset.is_multiple_of(block_size) || !length.is_multiple_of(block_size) {
+        return Err(NBD_EINVAL);
+    }
+    match request.offset.checked_add(length) {
+        Some(end) if end <= export.backend.size() => Ok(()),
+        _ if write => Err(NBD_ENOSPC),
+        _ => Err(NBD_EINVAL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{Backend, ExportStats, KIND_VOLUME};
+    use alloc::boxed::Box;
+    use alloc::string::String;
+
+    #[derive(Default)]
+    struct MockTransport {
+        input: Vec<u8>,
+        output: Vec<u8>,
+        tls: Option<(u64, u64)>,
+        /// Output length when TLS was started
+        tls_at: usize,
+        closed: bool,
+    }
+
+    impl Transport for MockTransport {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+            if self.input.is_empty() {
+                return Err(IoError::WouldBlock);
+            }
+            let length = buffer.len().min(self.input.len());
+            buffer[..length].copy_from_slice(&self.input[..length]);
+            self.input.drain(..length);
+            Ok(length)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+            self.output.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn close(&mut self) {
+            self.closed = true;
+        }
+    }
+
+    impl NbdTransport for MockTransport {
+        fn start_tls(&mut self, certificate_handle: u64, key_handle: u64) -> Result<(), i32> {
+            self.tls = Some((certificate_handle, key_handle));
+            self.tls_at = self.output.len();
+            Ok(())
+        }
+    }
+
+    struct MemoryBackend {
+        data: Vec<u8>,
+        block_size: u32,
+        flushes: u32,
+    }
+
+    impl Backend for MemoryBackend {
+        fn size(&self) -> u64 {
+            self.data.len() as u64
+        }
+
+        fn block_size(&self) -> u32 {
+            self.block_size
+        }
+
+        fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), i32> {
+            buffer.copy_from_slice(&self.data[offset as usize..offset as usize + buffer.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), i32> {
+            self.data[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), i32> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn export(id: u32, name: &str, read_only: bool) -> Export {
+        Export {
+            id,
+            name: String::from(name),
+            source: String::from(name),
+            kind: KIND_VOLUME,
+            read_only,
+            backend: Box::new(MemoryBackend { data: vec![0; 8192], block_size: 512, flushes: 0 }),
+            capability: 0,
+            owner: 0,
+            stats: ExportStats::default(),
+        }
+    }
+
+    fn option(option: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = NBD_IHAVEOPT.to_be_bytes().to_vec();
+        out.extend_from_slice(&option.to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn go(name: &str) -> Vec<u8> {
+        let mut data = (name.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&INFO_BLOCK_SIZE.to_be_bytes());
+        option(OPT_GO, &data)
+    }
+
+    fn request(command: u16, flags: u16, cookie: u64, offset: u64, length: u32) -> Vec<u8> {
+        let mut out = NBD_REQUEST_MAGIC.to_be_bytes().to_vec();
+        out.extend_from_slice(&flags.to_be_bytes());
+        out.extend_from_slice(&command.to_be_bytes());
+        out.extend_from_slice(&cookie.to_be_bytes());
+        out.extend_from_slice(&offset.to_be_bytes());
+        out.extend_from_slice(&length.to_be_bytes());
+        out
+    }
+
+    /// Option replies as (option, type, data)
+    fn option_replies(mut output: &[u8]) -> Vec<(u32, u32, Vec<u8>)> {
+        let mut replies = Vec::new();
+        while !output.is_empty() {
+            assert_eq!(read_u64(output, 0), Some(NBD_REPLY_MAGIC));
+            let length = read_u32(output, 16).unwrap() as usize;
+            replies.push((
+                read_u32(output, 8).unwrap(),
+                read_u32(output, 12).unwrap(),
+                output[20..20 + length].to_vec(),
+            ));
+            output = &output[20 + length..];
+        }
+        replies
+    }
+
+    fn exchange(session: &mut Session<MockTransport>, exports: &mut [Export], sent: &[u8]) -> Vec<u8> {
+        session.transport.input.extend_from_slice(sent);
+        session.poll(exports, &mut |export: &Export| export.name != "secret");
+        core::mem::take(&mut session.transport.output)
+    }
+
+    #[test]
+    fn test_negotiates_and_serves_requests() {
+        let mut exports = vec![export(1, "disk0", false), export(2, "secret", false), export(3, "iso", true)];
+        let mut session = Session::new(MockTransport::default(), Security::default());
+
+        let greeting = exchange(&mut session, &mut exports, &[]);
+        assert_eq!(greeting[..16], [NBD_MAGIC.to_be_bytes(), NBD_IHAVEOPT.to_be_bytes()].concat());
+        assert_eq!(greeting[16..], [0, 3]);
+
+        // LIST hides exports the client is not allowed to use
+        let mut sent = 3u32.to_be_bytes().to_vec();
+        sent.extend_from_slice(&option(OPT_LIST, &[]));
+        let replies = option_replies(&exchange(&mut session, &mut exports, &sent));
+        let names: Vec<&[u8]> =
+            replies.iter().filter(|reply| reply.1 == REP_SERVER).map(|reply| &reply.2[4..]).collect();
+        assert_eq!(names, [&b"disk0"[..], &b"iso"[..]]);
+        assert_eq!(replies.last().map(|reply| reply.1), Some(REP_ACK));
+
+        let replies = option_replies(&exchange(&mut session, &mut exports, &go("secret")));
+        assert_eq!(replies, [(OPT_GO, REP_ERR_POLICY, Vec::new())]);
+        let replies = option_replies(&exchange(&mut session, &mut exports, &go("nope")));
+        assert_eq!(replies, [(OPT_GO, REP_ERR_UNKNOWN, Vec::new())]);
+
+        let replies = option_replies(&exchange(&mut session, &mut exports, &go("iso")));
+        assert_eq!(replies.len(), 3);
+        let flags = TX_HAS_FLAGS | TX_READ_ONLY | TX_SEND_FLUSH | TX_SEND_FUA | TX_SEND_WRITE_ZEROES;
+        assert_eq!(replies[0].2, [&[0, 0][..], &8192u64.to_be_bytes(), &flags.to_be_bytes()].concat());
+        assert_eq!(replies[1].2[..6], [0, 3, 0, 0, 2, 0]);
+        assert_eq!(session.export(), Some(3));
+
+        // Writes to a read-only export fail, reads go through
+        let mut sent = request(CMD_WRITE, 0, 7, 0, 512);
+        sent.extend_from_slice(&[0xAA; 512]);
+        sent.extend_from_slice(&request(CMD_READ, 0, 8, 512, 512));
+        let output = exchange(&mut session, &mut exports, &sent);
+        assert_eq!(
+            output[..16],
+            [&NBD_SIMPLE_REPLY_MAGIC.to_be_bytes()[..], &1u32.to_be_bytes(), &7u64.to_be_bytes()].concat()
+        );
+        assert_eq!(read_u32(&output, 20), Some(0));
+        assert_eq!(output.len(), 32 + 512);
+        assert_eq!(exports[2].stats.errors, 1);
+
+        // The export goes away under the session
+        exports.remove(2);
+        let output = exchange(&mut session, &mut exports, &request(CMD_FLUSH, 0, 9, 0, 0));
+        assert_eq!(read_u32(&output, 4), Some(NBD_ESHUTDOWN));
+        assert!(session.is_closed());
+    }
+
+    #[test]
+    fn test_writes_within_block_bounds() {
+        let mut exports = vec![export(1, "disk0", false)];
+        let mut session = Session::new(MockTransport::default(), Security::default());
+        let mut sent = 3u32.to_be_bytes().to_vec();
+        sent.extend_from_slice(&option(OPT_EXPORT_NAME, b"disk0"));
+        let output = exchange(&mut session, &mut exports, &sent);
+        assert_eq!(output[18..26], 8192u64.to_be_bytes());
+        // NO_ZEROES was asked for: size and flags only
+        assert_eq!(output.len(), 18 + 10);
+
+        let mut sent = request(CMD_WRITE, CMD_FLAG_FUA, 1, 1024, 512);
+        sent.extend_from_slice(&[0x55; 512]);
+        sent.extend_from_slice(&request(CMD_WRITE_ZEROES, 0, 2, 1024, 256));
+        sent.extend_from_slice(&request(CMD_READ, 0, 3, 8192 - 512, 1024));
+        sent.extend_from_slice(&request(CMD_WRITE_ZEROES, 0, 4, 8192, 512));
+        sent.extend_from_slice(&request(CMD_READ, 0, 5, 1024, 512));
+        let output = exchange(&mut session, &mut exports, &sent);
+        let errors: Vec<u32> = (0..4).map(|index| read_u32(&output, index * 16 + 4).unwrap()).collect();
+        assert_eq!(errors, [0, NBD_EINVAL, NBD_EINVAL, NBD_ENOSPC]);
+        assert_eq!(output[64 + 16..], [0x55; 512]);
+        assert_eq!(exports[0].stats.bytes_written, 512);
+
+        let output = exchange(&mut session, &mut exports, &request(CMD_DISC, 0, 6, 0, 0));
+        assert!(output.is_empty());
+        assert!(session.is_closed() && session.transport.closed);
+    }
+
+    #[test]
+    fn test_requires_tls_before_options() {
+        let mut exports = vec![export(1, "disk0", false)];
+        let security = Security { tls: Some((4, 5)), required: true };
+        let mut session = Session::new(MockTransport::default(), security);
+        let mut sent = 1u32.to_be_bytes().to_vec();
+        sent.extend_from_slice(&go("disk0"));
+        let replies = option_replies(&exchange(&mut session, &mut exports, &sent)[18..]);
+        assert_eq!(replies, [(OPT_GO, REP_ERR_TLS_REQD, Vec::new())]);
+
+        let output = exchange(&mut session, &mut exports, &option(OPT_STARTTLS, &[]));
+        assert_eq!(option_replies(&output), [(OPT_STARTTLS, REP_ACK, Vec::new())]);
+        assert_eq!(session.transport.tls, Some((4, 5)));
+        assert_eq!(session.transport.tls_at, output.len());
+
+        let replies = option_replies(&exchange(&mut session, &mut exports, &go("disk0")));
+        assert_eq!(replies.last().map(|reply| reply.1), Some(REP_ACK));
+        assert_eq!(session.export(), Some(1));
+
+        // EXPORT_NAME without TLS cannot be refused politely
+        let mut session = Session::new(MockTransport::default(), security);
+        let mut sent = 1u32.to_be_bytes().to_vec();
+        sent.extend_from_slice(&option(OPT_EXPORT_NAME, b"disk0"));
+        exchange(&mut session, &mut exports, &sent);
+        assert!(session.is_closed());
+    }
+}
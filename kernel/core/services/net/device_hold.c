@@ -0,0 +1,328 @@
+/*
+ * Orion Operating System - Device Holds of the Network Server
+ *
+ * Request/reply calls to the I/O server over IPC ports. Calls are
+ * serialized and share the reply port with DEVICE_REVOKED events: an
+ * event arriving while a reply is awaited is taken in and the wait goes
+ * on. Replies start with a status, never 0x100, so the two cannot be
+ * mistaken. The pools of a revoked driver are only torn down by
+ * orion_device_hold_poll(), outside the lock, since tearing them down
+ * comes back here through orion_device_hold_detach().
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "device_hold.h"
+#include "rx_pool.h"
+#include <orion/klog.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+extern int ipc_send_message(or_cap_t port, const void *data, size_t size, uint64_t timeout_ns);
+extern int ipc_recv_message(or_cap_t port, void *buffer, size_t size, uint64_t timeout_ns);
+
+// The I/O server answers from memory; anything slower means it is gone
+#define IO_TIMEOUT_NS 1000000000ULL
+
+#define IO_REQUEST_MAX 64
+// Room for the LIST_DEVICES records of a well populated machine
+#define IO_REPLY_MAX 16384
+
+// LIST_DEVICES record up to the driver name: handle, vendor and device
+// ids, driver pid, name length
+#define IO_DEVICE_RECORD_SIZE 24
+
+// Purpose of the holds, as LIST_HOLDS shows it
+#define HOLD_PURPOSE "sockets"
+
+typedef struct {
+    uint64_t driver; // 0 when the slot is free
+    uint64_t device; // 0 when the I/O server does not know the driver
+    uint32_t hold;   // 0 when not held
+} hold_driver_t;
+
+static struct {
+    bool connected;
+    bool holding; // Sockets are open
+    uint64_t io_port;
+    uint64_t reply_port;
+    hold_driver_t drivers[ORION_DEVICE_HOLD_MAX_DRIVERS];
+    uint64_t revoked[ORION_DEVICE_HOLD_MAX_DRIVERS]; // Drivers whose pools are to be torn down
+    uint8_t request[IO_REQUEST_MAX];
+    uint8_t reply[IO_REPLY_MAX];
+} device_holds = {0};
+
+static spinlock_t hold_lock = SPINLOCK_INITIALIZER;
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static uint64_t get_u64(const uint8_t *p)
+{
+    return (uint64_t)get_u32(p) | ((uint64_t)get_u32(p + 4) << 32);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static void put_u64(uint8_t *p, uint64_t v)
+{
+    for (int i = 0; i < 8; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+/*
+ * Forget the hold named by a DEVICE_REVOKED event and queue its driver
+ * for teardown. Must be called with hold_lock held.
+ */
+static void hold_revoked(const uint8_t *event)
+{
+    uint64_t device = get_u64(event + 4);
+    uint32_t hold = get_u32(event + 12);
+
+    for (uint32_t i = 0; i < ORION_DEVICE_HOLD_MAX_DRIVERS; i++) {
+        hold_driver_t *entry = &device_holds.drivers[i];
+        if (!entry->driver || entry->hold == 0 || entry->hold != hold || entry->device != device) {
+            continue;
+        }
+        klog_warning(KLOG_CAT_KERNEL, "Device %llu revoked under open sockets, dropping its receive pools",
+                     (unsigned long long)device);
+        for (uint32_t j = 0; j < ORION_DEVICE_HOLD_MAX_DRIVERS; j++) {
+            if (!device_holds.revoked[j]) {
+                device_holds.revoked[j] = entry->driver;
+                break;
+            }
+        }
+        memset(entry, 0, sizeof(*entry));
+        return;
+    }
+}
+
+static bool hold_is_event(int received)
+{
+    return received == ORION_DEVICE_HOLD_EVENT_SIZE && get_u32(device_holds.reply) == ORION_IO_EVENT_DEVICE_REVOKED;
+}
+
+/*
+ * Send the request staged in device_holds.request and wait for the reply.
+ * Returns the payload length past the status word, or the negative
+ * status. Must be called with hold_lock held.
+ */
+static int hold_call(size_t request_len)
+{
+    if (!device_holds.connected) {
+        return -1;
+    }
+
+    int result = ipc_send_message(device_holds.io_port, device_holds.request, request_len, IO_TIMEOUT_NS);
+    if (result < 0) {
+        klog_error(KLOG_CAT_KERNEL, "I/O server request failed: %d", result);
+        return result;
+    }
+
+    int received;
+    for (;;) {
+        received = ipc_recv_message(device_holds.reply_port, device_holds.reply, sizeof(device_holds.reply),
+                                    IO_TIMEOUT_NS);
+        if (!hold_is_event(received)) {
+            break;
+        }
+        hold_revoked(device_holds.reply);
+    }
+    if (received < 4) {
+        klog_error(KLOG_CAT_KERNEL, "I/O server reply missing: %d", received);
+        return received < 0 ? received : -1;
+    }
+
+    int32_t status = (int32_t)get_u32(device_holds.reply);
+    if (status != 0) {
+        return status;
+    }
+    return received - 4;
+}
+
+// Device handle of the device the driver process drives, 0 if none.
+// Must be called with hold_lock held.
+static uint64_t hold_lookup_device(uint64_t driver)
+{
+    put_u32(device_holds.request, ORION_IO_OP_LIST_DEVICES);
+    int length = hold_call(4);
+    if (length < 0) {
+        return 0;
+    }
+
+    const uint8_t *record = device_holds.reply + 4;
+    const uint8_t *end = record + length;
+    while (end - record >= IO_DEVICE_RECORD_SIZE) {
+        uint64_t handle = get_u64(record);
+        uint64_t pid = get_u64(record + 12);
+        uint32_t name_len = get_u32(record + 20);
+        if (pid == driver) {
+            return handle;
+        }
+        if ((size_t)(end - record - IO_DEVICE_RECORD_SIZE) < name_len) {
+            break;
+        }
+        record += IO_DEVICE_RECORD_SIZE + name_len;
+    }
+    return 0;
+}
+
+// Must be called with hold_lock held
+static void hold_take(hold_driver_t *entry)
+{
+    if (!entry->device || entry->hold) {
+        return;
+    }
+
+    size_t purpose_len = strlen(HOLD_PURPOSE);
+    put_u32(device_holds.request, ORION_IO_OP_DEVICE_HOLD);
+    put_u64(device_holds.request + 4, entry->device);
+    put_u32(device_holds.request + 12, (uint32_t)purpose_len);
+    memcpy(device_holds.request + 16, HOLD_PURPOSE, purpose_len);
+
+    int length = hold_call(16 + purpose_len);
+    if (length >= 4) {
+        entry->hold = get_u32(device_holds.reply + 4);
+    } else {
+        klog_warning(KLOG_CAT_KERNEL, "Device %llu not held: %d", (unsigned long long)entry->device, length);
+    }
+}
+
+// Must be called with hold_lock held
+static void hold_give_back(hold_driver_t *entry)
+{
+    if (!entry->hold) {
+        return;
+    }
+
+    put_u32(device_holds.request, ORION_IO_OP_DEVICE_RELEASE);
+    put_u32(device_holds.request + 4, entry->hold);
+    entry->hold = 0;
+    (void)hold_call(8);
+}
+
+static hold_driver_t *hold_find(uint64_t driver)
+{
+    for (uint32_t i = 0; i < ORION_DEVICE_HOLD_MAX_DRIVERS; i++) {
+        if (device_holds.drivers[i].driver == driver) {
+            return &device_holds.drivers[i];
+        }
+    }
+    return NULL;
+}
+
+int orion_device_hold_init(uint64_t io_port, uint64_t reply_port)
+{
+    if (io_port == 0 || reply_port == 0) {
+        return -1;
+    }
+
+    spinlock_acquire(&hold_lock);
+    device_holds.io_port = io_port;
+    device_holds.reply_port = reply_port;
+    device_holds.connected = true;
+    spinlock_release(&hold_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "Device holds connected to the I/O server (port %llu)", (unsigned long long)io_port);
+    return 0;
+}
+
+void orion_device_hold_attach(uint64_t driver)
+{
+    if (driver == 0) {
+        return;
+    }
+
+    spinlock_acquire(&hold_lock);
+    hold_driver_t *entry = hold_find(driver);
+    if (!entry) {
+        entry = hold_find(0);
+        if (entry) {
+            entry->driver = driver;
+            entry->device = hold_lookup_device(driver);
+            if (device_holds.holding) {
+                hold_take(entry);
+            }
+        } else {
+            klog_warning(KLOG_CAT_KERNEL, "Too many NIC drivers, %llu left unheld", (unsigned long long)driver);
+        }
+    }
+    spinlock_release(&hold_lock);
+}
+
+void orion_device_hold_detach(uint64_t driver)
+{
+    if (driver == 0) {
+        return;
+    }
+
+    spinlock_acquire(&hold_lock);
+    hold_driver_t *entry = hold_find(driver);
+    if (entry) {
+        hold_give_back(entry);
+        memset(entry, 0, sizeof(*entry));
+    }
+    spinlock_release(&hold_lock);
+}
+
+void orion_device_hold_sockets(uint32_t open)
+{
+    spinlock_acquire(&hold_lock);
+    bool holding = open > 0;
+    if (holding != device_holds.holding) {
+        device_holds.holding = holding;
+        for (uint32_t i = 0; i < ORION_DEVICE_HOLD_MAX_DRIVERS; i++) {
+            hold_driver_t *entry = &device_holds.drivers[i];
+            if (!entry->driver) {
+                continue;
+            }
+            if (holding) {
+                hold_take(entry);
+            } else {
+                hold_give_back(entry);
+            }
+        }
+    }
+    spinlock_release(&hold_lock);
+}
+
+void orion_device_hold_poll(void (*unmap)(void *base))
+{
+    uint64_t revoked[ORION_DEVICE_HOLD_MAX_DRIVERS];
+
+    spinlock_acquire(&hold_lock);
+    if (device_holds.connected) {
+        for (;;) {
+            int received =
+                ipc_recv_message(device_holds.reply_port, device_holds.reply, sizeof(device_holds.reply), 0);
+            if (received <= 0) {
+                break;
+            }
+            if (hold_is_event(received)) {
+                hold_revoked(device_holds.reply);
+            }
+        }
+    }
+    memcpy(revoked, device_holds.revoked, sizeof(revoked));
+    memset(device_holds.revoked, 0, sizeof(device_holds.revoked));
+    spinlock_release(&hold_lock);
+
+    for (uint32_t i = 0; i < ORION_DEVICE_HOLD_MAX_DRIVERS; i++) {
+        if (revoked[i]) {
+            orion_rx_pool_release(revoked[i], unmap);
+        }
+    }
+}
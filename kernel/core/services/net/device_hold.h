@@ -0,0 +1,80 @@
+/*
+ * Orion Operating System - Device Holds of the Network Server
+ *
+ * While sockets are open, the network server holds the devices of the NIC
+ * drivers feeding it through receive pools, so that the I/O server does
+ * not unload them under live connections (DEVICE_HOLD and DEVICE_RELEASE,
+ * see services/io/src/protocol.rs). A driver is known by the process that
+ * registered its pools and its device looked up with LIST_DEVICES. When a
+ * driver is unloaded anyway, the I/O server sends DEVICE_REVOKED to the
+ * reply port the holds were taken from and the driver's pools are torn
+ * down.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_DEVICE_HOLD_H
+#define ORION_DEVICE_HOLD_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// I/O server opcodes and events
+#define ORION_IO_OP_LIST_DEVICES 8
+#define ORION_IO_OP_DEVICE_HOLD 15
+#define ORION_IO_OP_DEVICE_RELEASE 16
+#define ORION_IO_EVENT_DEVICE_REVOKED 0x100
+
+#define ORION_DEVICE_HOLD_EVENT_SIZE 16
+
+// NIC drivers followed at once
+#define ORION_DEVICE_HOLD_MAX_DRIVERS 16
+
+    /**
+     * @brief Connect to the I/O server
+     * @param io_port Port capability of the I/O server
+     * @param reply_port Port owned by the network server receiving replies
+     *                   and DEVICE_REVOKED events, used for nothing else
+     * @return 0 on success, negative value on error
+     */
+    int orion_device_hold_init(uint64_t io_port, uint64_t reply_port);
+
+    /**
+     * @brief Follow a NIC driver that registered its first receive pool
+     * @param driver Process of the driver
+     */
+    void orion_device_hold_attach(uint64_t driver);
+
+    /**
+     * @brief Stop following a NIC driver whose last receive pool is gone,
+     *        giving its hold back
+     * @param driver Process of the driver
+     */
+    void orion_device_hold_detach(uint64_t driver);
+
+    /**
+     * @brief Report the number of open sockets: the devices of the drivers
+     *        followed are held while it is not zero
+     * @param open Sockets open
+     */
+    void orion_device_hold_sockets(uint32_t open);
+
+    /**
+     * @brief Take in the DEVICE_REVOKED events waiting on the reply port
+     * @param unmap Unmaps the region of a pool of a revoked driver
+     */
+    void orion_device_hold_poll(void (*unmap)(void *base));
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_DEVICE_HOLD_H
@@ -0,0 +1,119 @@
+/*
+ * Orion Operating System - Network Server Health Checks Implementation
+ *
+ * Runs the checks of the network server against the stack state and
+ * encodes their results for the health server.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "health_ipc.h"
+#include "network_architecture.h"
+#include <orion/mm.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define HEALTH_STATUS_OK 0
+#define HEALTH_STATUS_ENOMEM -12
+#define HEALTH_STATUS_EINVAL -22
+
+#define HEALTH_MAX_LISTED 64
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static size_t health_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+static size_t put_string(uint8_t *out, const char *text)
+{
+    size_t len = strlen(text);
+    put_u32(out, (uint32_t)len);
+    memcpy(out + 4, text, len);
+    return 4 + len;
+}
+
+// Append one check result at out, or nothing when it does not fit
+static size_t put_check(uint8_t *out, size_t room, uint8_t probe, const char *name, const char *detail)
+{
+    if (room < 2 + 4 + strlen(name) + 4 + strlen(detail)) {
+        return 0;
+    }
+    out[0] = probe;
+    out[1] = detail[0] == '\0';
+    size_t len = 2 + put_string(out + 2, name);
+    return len + put_string(out + len, detail);
+}
+
+// Number of interfaces up, negative when they cannot be listed
+static int interfaces_up(void)
+{
+    orion_net_iface_config_t *interfaces = kmalloc(HEALTH_MAX_LISTED * sizeof(orion_net_iface_config_t));
+    if (!interfaces) {
+        return -1;
+    }
+    int count = orion_net_get_interfaces(interfaces, HEALTH_MAX_LISTED);
+    int up = 0;
+    for (int i = 0; i < count; i++) {
+        if (interfaces[i].state == ORION_NET_IFACE_UP) {
+            up++;
+        }
+    }
+    kfree(interfaces);
+    return up;
+}
+
+static size_t health_check(uint8_t *reply, size_t reply_capacity)
+{
+    if (reply_capacity < 8) {
+        return health_reply(reply, HEALTH_STATUS_EINVAL, 0);
+    }
+    int up = interfaces_up();
+    if (up < 0) {
+        return health_reply(reply, HEALTH_STATUS_ENOMEM, 0);
+    }
+
+    const char *stack = orion_net_check_health() == 0 ? "" : "stack not initialized or missing a driver";
+    const char *link = up > 0 ? "" : "no interface up";
+
+    uint8_t *out = reply + 4;
+    size_t room = reply_capacity - 4;
+    size_t len = 4;
+    uint32_t count = 0;
+    size_t written = put_check(out + len, room - len, ORION_HEALTH_PROBE_LIVENESS, "stack", stack);
+    count += written != 0;
+    len += written;
+    written = put_check(out + len, room - len, ORION_HEALTH_PROBE_READINESS, "link", link);
+    count += written != 0;
+    len += written;
+    put_u32(out, count);
+    return health_reply(reply, HEALTH_STATUS_OK, len);
+}
+
+size_t orion_net_health_ipc_handle(const uint8_t *request, size_t request_len, uint8_t *reply,
+                                   size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 4) {
+        return 0;
+    }
+    if (request_len < 4 || get_u32(request) != ORION_HEALTH_OP_CHECK) {
+        return health_reply(reply, HEALTH_STATUS_EINVAL, 0);
+    }
+    return health_check(reply, reply_capacity);
+}
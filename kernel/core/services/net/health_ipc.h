@@ -0,0 +1,52 @@
+/*
+ * Orion Operating System - Network Server Health Checks
+ *
+ * Answers the CHECK request of the health server (see
+ * lib/orion_health): liveness "stack", whether the stack is initialized
+ * with its drivers and interfaces in place, and readiness "link",
+ * whether an interface is up. Same framing as socket_ipc.h; the opcode
+ * is far from the other ranges so the message loop can hand it over
+ * first.
+ *
+ *   CHECK  (empty)  -> count:u32 {probe:u8 passed:u8 name detail}*
+ *
+ * Strings are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_HEALTH_IPC_H
+#define ORION_NET_HEALTH_IPC_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_HEALTH_OP_CHECK 0x48450001U
+
+#define ORION_HEALTH_PROBE_LIVENESS 1
+#define ORION_HEALTH_PROBE_READINESS 2
+
+    /**
+     * @brief Handle one health request
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_net_health_ipc_handle(const uint8_t *request, size_t request_len, uint8_t *reply,
+                                       size_t reply_capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_HEALTH_IPC_H
@@ -0,0 +1,188 @@
+/*
+ * Orion Operating System - Interface Configuration IPC Implementation
+ *
+ * Serializes interface state for management tools and applies their
+ * state and MTU changes through orion_net_configure_interface().
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "iface_ipc.h"
+#include "netns.h"
+#include "network_architecture.h"
+#include <orion/klog.h>
+#include <orion/mm.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define IFACE_STATUS_OK 0
+#define IFACE_STATUS_EPERM -1
+#define IFACE_STATUS_ENOENT -2
+#define IFACE_STATUS_ENOMEM -12
+#define IFACE_STATUS_EINVAL -22
+
+#define IFACE_MAX_LISTED 64
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static void put_u64(uint8_t *p, uint64_t v)
+{
+    for (int i = 0; i < 8; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static size_t iface_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+static void iface_encode(const orion_net_iface_config_t *iface, uint8_t *out)
+{
+    memset(out, 0, ORION_IFACE_RECORD_SIZE);
+    memcpy(out, iface->name, sizeof(iface->name));
+    out[31] = '\0';
+    put_u32(out + 32, (uint32_t)iface->type);
+    put_u32(out + 36, (uint32_t)iface->state);
+    put_u32(out + 40, iface->mtu);
+    put_u32(out + 44, iface->flags);
+    memcpy(out + 48, iface->mac_addr, 6);
+
+    const uint64_t counters[8] = {
+        iface->stats.rx_packets, iface->stats.tx_packets,
+        iface->stats.rx_bytes, iface->stats.tx_bytes,
+        iface->stats.rx_errors, iface->stats.tx_errors,
+        iface->stats.rx_dropped, iface->stats.tx_dropped,
+    };
+    for (int i = 0; i < 8; i++) {
+        put_u64(out + 56 + 8 * i, counters[i]);
+    }
+}
+
+// A namespace's interface, under the name it has there
+static size_t iface_list_namespace(uint32_t ns, uint8_t *reply, size_t reply_capacity)
+{
+    char host_name[32];
+    if (orion_netns_host_interface(ns, host_name) != 0) {
+        return iface_reply(reply, IFACE_STATUS_ENOMEM, 0);
+    }
+    orion_net_iface_config_t *iface = orion_net_get_interface(host_name);
+    if (!iface || reply_capacity < 4 + ORION_IFACE_RECORD_SIZE) {
+        return iface_reply(reply, IFACE_STATUS_OK, 0);
+    }
+    orion_net_iface_config_t seen;
+    memcpy(&seen, iface, sizeof(seen));
+    memset(seen.name, 0, sizeof(seen.name));
+    strcpy(seen.name, ORION_NETNS_IFACE);
+    iface_encode(&seen, reply + 4);
+    return iface_reply(reply, IFACE_STATUS_OK, ORION_IFACE_RECORD_SIZE);
+}
+
+static size_t iface_list(uint8_t *reply, size_t reply_capacity)
+{
+    orion_net_iface_config_t *interfaces = kmalloc(IFACE_MAX_LISTED * sizeof(orion_net_iface_config_t));
+    if (!interfaces) {
+        return iface_reply(reply, IFACE_STATUS_ENOMEM, 0);
+    }
+
+    int count = orion_net_get_interfaces(interfaces, IFACE_MAX_LISTED);
+    size_t len = 0;
+    for (int i = 0; i < count && 4 + len + ORION_IFACE_RECORD_SIZE <= reply_capacity; i++) {
+        iface_encode(&interfaces[i], reply + 4 + len);
+        len += ORION_IFACE_RECORD_SIZE;
+    }
+
+    kfree(interfaces);
+    return iface_reply(reply, IFACE_STATUS_OK, len);
+}
+
+static int iface_configure(uint32_t ns, const uint8_t *args, size_t args_len)
+{
+    if (args_len < 44) {
+        return IFACE_STATUS_EINVAL;
+    }
+
+    char name[32];
+    memcpy(name, args, sizeof(name));
+    name[31] = '\0';
+    // In a namespace, "eth0" is the host side interface and nothing else exists
+    if (ns != 0) {
+        if (strcmp(name, ORION_NETNS_IFACE) != 0) {
+            return IFACE_STATUS_ENOENT;
+        }
+        if (orion_netns_host_interface(ns, name) != 0) {
+            return IFACE_STATUS_ENOMEM;
+        }
+    }
+    uint32_t mask = get_u32(args + 32);
+    uint32_t state = get_u32(args + 36);
+    uint32_t mtu = get_u32(args + 40);
+
+    if ((mask & ORION_IFACE_SET_STATE) && state != ORION_NET_IFACE_UP && state != ORION_NET_IFACE_DOWN) {
+        return IFACE_STATUS_EINVAL;
+    }
+    if ((mask & ORION_IFACE_SET_MTU) && (mtu < ORION_IFACE_MIN_MTU || mtu > ORION_IFACE_MAX_MTU)) {
+        return IFACE_STATUS_EINVAL;
+    }
+
+    // Configure an updated copy so that unknown names are not created
+    orion_net_iface_config_t *current = orion_net_get_interface(name);
+    if (!current) {
+        return IFACE_STATUS_ENOENT;
+    }
+    orion_net_iface_config_t config;
+    memcpy(&config, current, sizeof(config));
+    if (mask & ORION_IFACE_SET_STATE) {
+        config.state = (orion_net_iface_state_t)state;
+    }
+    if (mask & ORION_IFACE_SET_MTU) {
+        config.mtu = mtu;
+    }
+
+    if (orion_net_configure_interface(name, &config) != 0) {
+        return IFACE_STATUS_EINVAL;
+    }
+    klog_info(KLOG_CAT_KERNEL, "Interface %s reconfigured over IPC (mask 0x%x)", name, mask);
+    return IFACE_STATUS_OK;
+}
+
+size_t orion_iface_ipc_handle(uint64_t sender, bool admin, const uint8_t *request, size_t request_len,
+                              uint8_t *reply, size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 4) {
+        return 0;
+    }
+    if (request_len < 4) {
+        return iface_reply(reply, IFACE_STATUS_EINVAL, 0);
+    }
+
+    uint32_t ns = orion_netns_of(sender);
+    switch (get_u32(request)) {
+    case ORION_IFACE_OP_LIST:
+        return ns ? iface_list_namespace(ns, reply, reply_capacity) : iface_list(reply, reply_capacity);
+
+    case ORION_IFACE_OP_CONFIGURE:
+        if (!admin) {
+            return iface_reply(reply, IFACE_STATUS_EPERM, 0);
+        }
+        return iface_reply(reply, iface_configure(ns, request + 4, request_len - 4), 0);
+
+    default:
+        return iface_reply(reply, IFACE_STATUS_EINVAL, 0);
+    }
+}
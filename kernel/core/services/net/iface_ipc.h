@@ -0,0 +1,63 @@
+/*
+ * Orion Operating System - Interface Configuration IPC Interface
+ *
+ * Interface queries and configuration offered by the network server to
+ * management tools. Same framing as socket_ipc.h; opcodes start at 16 so
+ * that the message loop can dispatch on the opcode alone.
+ *
+ *   LIST       (none)                                  -> records
+ *   CONFIGURE  name[32] mask:u32 state:u32 mtu:u32     -> (empty)
+ *
+ * A LIST record is ORION_IFACE_RECORD_SIZE bytes: name[32] type:u32
+ * state:u32 mtu:u32 flags:u32 mac[6] pad[2], then rx/tx packets, rx/tx
+ * bytes, rx/tx errors and rx/tx dropped as u64. CONFIGURE only applies
+ * the fields selected by mask and requires an administrative caller.
+ *
+ * A sender in a network namespace (see netns.h) lists and configures its
+ * own interface only, under the name "eth0".
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_IFACE_IPC_H
+#define ORION_IFACE_IPC_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_IFACE_OP_LIST 16
+#define ORION_IFACE_OP_CONFIGURE 17
+
+#define ORION_IFACE_SET_STATE (1U << 0)
+#define ORION_IFACE_SET_MTU (1U << 1)
+
+#define ORION_IFACE_RECORD_SIZE 120
+#define ORION_IFACE_MIN_MTU 68
+#define ORION_IFACE_MAX_MTU 9216
+
+    /**
+     * @brief Handle one interface request
+     * @param sender PID of the sender
+     * @param admin Whether the sender holds administrative rights on the network server
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_iface_ipc_handle(uint64_t sender, bool admin, const uint8_t *request, size_t request_len,
+                                  uint8_t *reply, size_t reply_capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_IFACE_IPC_H
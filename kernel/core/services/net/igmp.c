@@ -0,0 +1,667 @@
+/*
+ * Orion Operating System - IGMP Multicast Group Membership Implementation
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "igmp.h"
+#include <orion/klog.h>
+#include <orion/spinlock.h>
+#include <orion/wallclock.h>
+#include <string.h>
+
+extern uint64_t security_get_random(void);
+
+/* ============================================================================
+ * Protocol Constants
+ * ============================================================================ */
+
+#define IGMP_QUERY 0x11
+#define IGMP_V1_REPORT 0x12
+#define IGMP_V2_REPORT 0x16
+#define IGMP_V2_LEAVE 0x17
+#define IGMP_V3_REPORT 0x22
+
+#define IGMPV3_MODE_IS_EXCLUDE 2
+#define IGMPV3_CHANGE_TO_INCLUDE 3
+#define IGMPV3_CHANGE_TO_EXCLUDE 4
+
+#define IGMP_ETH_HLEN 14
+#define IGMP_IP_HLEN 24 // With the Router Alert option
+#define IGMP_V2_LEN 8
+#define IGMP_V3_QUERY_LEN 12
+#define IGMP_V3_RECORD_LEN 8
+#define IGMP_FRAME_MAX (IGMP_ETH_HLEN + IGMP_IP_HLEN + 8 + IGMP_V3_RECORD_LEN * ORION_IGMP_MAX_GROUPS)
+
+#define IGMP_TOS_INTERNETWORK_CONTROL 0xC0
+#define IGMP_NS_PER_UNIT 100000000ULL // Maximum response times are in tenths of a second
+#define IGMP_V1_MAX_RESP 100          // IGMPv1 queries carry none: 10 s
+#define IGMP_V2_UNSOLICITED_NS 10000000000ULL
+#define IGMP_V3_UNSOLICITED_NS 1000000000ULL
+// Robustness Variable * Query Interval + Query Response Interval
+#define IGMP_OLDER_QUERIER_NS ((ORION_IGMP_ROBUSTNESS * 125ULL + 10) * 1000000000ULL)
+
+typedef struct
+{
+    uint32_t group;     // 0 for a free slot
+    uint32_t users;     // Sockets holding the group; 0 while its leave is being announced
+    uint8_t changes;    // State-change reports left to send
+    bool last_reporter; // IGMPv1/v2: no other host reported since us, so we send the leave
+    uint64_t report_at; // Next report, 0 if none is pending
+} igmp_group_t;
+
+typedef struct
+{
+    orion_net_driver_t *driver; // NULL for a free slot
+    uint32_t ifaddr;
+    uint8_t mac[6];
+    uint64_t v1_querier_until;  // Older version queriers heard, until then
+    uint64_t v2_querier_until;
+    uint64_t general_report_at; // IGMPv3 answer to a general query, 0 if none
+    igmp_group_t groups[ORION_IGMP_MAX_GROUPS];
+} igmp_iface_t;
+
+static igmp_iface_t igmp_ifaces[ORION_IGMP_MAX_IFACES];
+static orion_igmp_stats_t igmp_stats;
+static uint16_t igmp_ip_id;
+static spinlock_t igmp_lock = SPINLOCK_INITIALIZER;
+
+/* ============================================================================
+ * Helpers
+ * ============================================================================ */
+
+void orion_ip_multicast_mac(uint32_t group, uint8_t mac[6])
+{
+    mac[0] = 0x01;
+    mac[1] = 0x00;
+    mac[2] = 0x5E;
+    mac[3] = (uint8_t)((group >> 16) & 0x7F);
+    mac[4] = (uint8_t)(group >> 8);
+    mac[5] = (uint8_t)group;
+}
+
+static uint64_t igmp_random_delay(uint64_t max_ns)
+{
+    return max_ns ? security_get_random() % max_ns : 0;
+}
+
+static void put_be32(uint8_t *p, uint32_t v)
+{
+    p[0] = (uint8_t)(v >> 24);
+    p[1] = (uint8_t)(v >> 16);
+    p[2] = (uint8_t)(v >> 8);
+    p[3] = (uint8_t)v;
+}
+
+// Internet checksum, zero over a message that carries a valid one
+static uint16_t igmp_checksum(const uint8_t *data, size_t len)
+{
+    uint32_t sum = 0;
+    for (size_t i = 0; i + 1 < len; i += 2) {
+        sum += (uint32_t)((data[i] << 8) | data[i + 1]);
+    }
+    if (len % 2) {
+        sum += (uint32_t)data[len - 1] << 8;
+    }
+    while (sum >> 16) {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    return (uint16_t)~sum;
+}
+
+static uint32_t get_be32(const uint8_t *p)
+{
+    return ((uint32_t)p[0] << 24) | ((uint32_t)p[1] << 16) | ((uint32_t)p[2] << 8) | p[3];
+}
+
+// Host Compatibility Mode of an interface
+static int igmp_version(const igmp_iface_t *iface, uint64_t now)
+{
+    if (now < iface->v1_querier_until) {
+        return 1;
+    }
+    if (now < iface->v2_querier_until) {
+        return 2;
+    }
+    return 3;
+}
+
+// Called with igmp_lock held
+static igmp_iface_t *igmp_find_iface(uint32_t ifaddr)
+{
+    for (int i = 0; i < ORION_IGMP_MAX_IFACES; i++) {
+        igmp_iface_t *iface = &igmp_ifaces[i];
+        if (iface->driver && (ifaddr == 0 || iface->ifaddr == ifaddr)) {
+            return iface;
+        }
+    }
+    return NULL;
+}
+
+static igmp_group_t *igmp_find_group(igmp_iface_t *iface, uint32_t group)
+{
+    for (int i = 0; i < ORION_IGMP_MAX_GROUPS; i++) {
+        if (iface->groups[i].group == group) {
+            return &iface->groups[i];
+        }
+    }
+    return NULL;
+}
+
+// Schedule an answer to a query unless one is due sooner
+static void igmp_schedule(uint64_t *report_at, uint64_t now, uint64_t max_ns)
+{
+    uint64_t at = now + igmp_random_delay(max_ns);
+    if (!*report_at || at < *report_at) {
+        *report_at = at;
+    }
+}
+
+// Ethernet addresses of the groups the driver must accept; called with igmp_lock held
+static size_t igmp_filter_list(const igmp_iface_t *iface, uint8_t *addresses)
+{
+    size_t count = 0;
+    orion_ip_multicast_mac(ORION_IGMP_ALL_HOSTS, addresses);
+    count++;
+    for (int i = 0; i < ORION_IGMP_MAX_GROUPS; i++) {
+        if (iface->groups[i].group && iface->groups[i].users) {
+            orion_ip_multicast_mac(iface->groups[i].group, addresses + 6 * count);
+            count++;
+        }
+    }
+    return count;
+}
+
+static void igmp_program_filter(uint32_t ifaddr)
+{
+    uint8_t addresses[6 * (ORION_IGMP_MAX_GROUPS + 1)];
+    orion_net_driver_t *driver = NULL;
+    size_t count = 0;
+
+    spinlock_acquire(&igmp_lock);
+    igmp_iface_t *iface = igmp_find_iface(ifaddr);
+    if (iface) {
+        driver = iface->driver;
+        count = igmp_filter_list(iface, addresses);
+    }
+    spinlock_release(&igmp_lock);
+
+    // Drivers without a multicast filter accept every group already
+    if (driver && driver->set_multicast && driver->set_multicast(driver, addresses, count) != 0) {
+        klog_warning(KLOG_CAT_KERNEL, "IGMP: %s rejected the multicast list, accepting all groups",
+                     driver->name);
+        driver->set_multicast(driver, NULL, 0);
+    }
+}
+
+/* ============================================================================
+ * Message Construction
+ * ============================================================================ */
+
+// Ethernet and IPv4 headers (TTL 1, Router Alert); returns the IGMP message offset
+static size_t igmp_frame_header(uint8_t *frame, igmp_iface_t *iface, uint32_t dst_ip, size_t igmp_len,
+                                uint8_t tos)
+{
+    orion_ip_multicast_mac(dst_ip, frame);
+    memcpy(frame + 6, iface->mac, 6);
+    frame[12] = 0x08;
+    frame[13] = 0x00;
+
+    uint8_t *ip = frame + IGMP_ETH_HLEN;
+    uint16_t total = (uint16_t)(IGMP_IP_HLEN + igmp_len);
+    uint16_t id = igmp_ip_id++;
+    ip[0] = 0x46; // IPv4, 6 words of header
+    ip[1] = tos;
+    ip[2] = (uint8_t)(total >> 8);
+    ip[3] = (uint8_t)total;
+    ip[4] = (uint8_t)(id >> 8);
+    ip[5] = (uint8_t)id;
+    ip[6] = 0;
+    ip[7] = 0;
+    ip[8] = 1; // Never forwarded
+    ip[9] = ORION_IP_PROTOCOL_IGMP;
+    ip[10] = 0;
+    ip[11] = 0;
+    put_be32(ip + 12, iface->ifaddr);
+    put_be32(ip + 16, dst_ip);
+    ip[20] = 0x94; // Router Alert
+    ip[21] = 0x04;
+    ip[22] = 0;
+    ip[23] = 0;
+    uint16_t checksum = igmp_checksum(ip, IGMP_IP_HLEN);
+    ip[10] = (uint8_t)(checksum >> 8);
+    ip[11] = (uint8_t)checksum;
+
+    return IGMP_ETH_HLEN + IGMP_IP_HLEN;
+}
+
+static void igmp_set_checksum(uint8_t *message, size_t len)
+{
+    message[2] = 0;
+    message[3] = 0;
+    uint16_t checksum = igmp_checksum(message, len);
+    message[2] = (uint8_t)(checksum >> 8);
+    message[3] = (uint8_t)checksum;
+}
+
+// IGMPv1/v2 report or leave
+static size_t igmp_build_v2(uint8_t *frame, igmp_iface_t *iface, uint8_t type, uint32_t group)
+{
+    uint32_t dst_ip = type == IGMP_V2_LEAVE ? ORION_IGMP_ALL_ROUTERS : group;
+    size_t offset = igmp_frame_header(frame, iface, dst_ip, IGMP_V2_LEN, 0);
+    uint8_t *message = frame + offset;
+    message[0] = type;
+    message[1] = 0;
+    put_be32(message + 4, group);
+    igmp_set_checksum(message, IGMP_V2_LEN);
+    return offset + IGMP_V2_LEN;
+}
+
+// IGMPv3 report of one group, or of every group held when group is 0
+static size_t igmp_build_v3(uint8_t *frame, igmp_iface_t *iface, uint8_t record_type, uint32_t group)
+{
+    uint8_t *message = frame + IGMP_ETH_HLEN + IGMP_IP_HLEN;
+    size_t records = 0;
+    for (int i = 0; i < ORION_IGMP_MAX_GROUPS; i++) {
+        const igmp_group_t *entry = &iface->groups[i];
+        bool wanted = group ? entry->group == group : entry->group && entry->users;
+        if (!wanted) {
+            continue;
+        }
+        uint8_t *record = message + 8 + IGMP_V3_RECORD_LEN * records;
+        record[0] = record_type;
+        record[1] = 0; // No auxiliary data
+        record[2] = 0; // No sources: EXCLUDE({}) or INCLUDE({})
+        record[3] = 0;
+        put_be32(record + 4, entry->group);
+        records++;
+    }
+    if (!records) {
+        return 0;
+    }
+
+    size_t len = 8 + IGMP_V3_RECORD_LEN * records;
+    igmp_frame_header(frame, iface, ORION_IGMPV3_ROUTERS, len, IGMP_TOS_INTERNETWORK_CONTROL);
+    message[0] = IGMP_V3_REPORT;
+    message[1] = 0;
+    message[4] = 0;
+    message[5] = 0;
+    message[6] = (uint8_t)(records >> 8);
+    message[7] = (uint8_t)records;
+    igmp_set_checksum(message, len);
+    return IGMP_ETH_HLEN + IGMP_IP_HLEN + len;
+}
+
+// State-change report of a group after a join or leave; called with igmp_lock held
+static size_t igmp_build_change(uint8_t *frame, igmp_iface_t *iface, igmp_group_t *entry, int version)
+{
+    bool joined = entry->users > 0;
+    if (version == 3) {
+        igmp_stats.reports_sent += joined;
+        igmp_stats.leaves_sent += !joined;
+        return igmp_build_v3(frame, iface, joined ? IGMPV3_CHANGE_TO_EXCLUDE : IGMPV3_CHANGE_TO_INCLUDE,
+                             entry->group);
+    }
+    if (joined) {
+        entry->last_reporter = true;
+        igmp_stats.reports_sent++;
+        return igmp_build_v2(frame, iface, version == 1 ? IGMP_V1_REPORT : IGMP_V2_REPORT, entry->group);
+    }
+    // IGMPv1 has no leave; in IGMPv2 only the last host to report sends one
+    if (version == 1 || !entry->last_reporter) {
+        return 0;
+    }
+    igmp_stats.leaves_sent++;
+    return igmp_build_v2(frame, iface, IGMP_V2_LEAVE, entry->group);
+}
+
+/* ============================================================================
+ * Timers
+ * ============================================================================ */
+
+// Build the next due report of an interface into frame; called with igmp_lock held
+static size_t igmp_next_due(igmp_iface_t *iface, uint8_t *frame, uint64_t now)
+{
+    int version = igmp_version(iface, now);
+
+    if (iface->general_report_at && now >= iface->general_report_at) {
+        iface->general_report_at = 0;
+        size_t len = igmp_build_v3(frame, iface, IGMPV3_MODE_IS_EXCLUDE, 0);
+        if (len) {
+            igmp_stats.reports_sent++;
+            return len;
+        }
+    }
+
+    for (int i = 0; i < ORION_IGMP_MAX_GROUPS; i++) {
+        igmp_group_t *entry = &iface->groups[i];
+        if (!entry->group || !entry->report_at || now < entry->report_at) {
+            continue;
+        }
+
+        size_t len;
+        if (entry->changes) {
+            len = igmp_build_change(frame, iface, entry, version);
+            entry->changes--;
+            uint64_t interval = version == 3 ? IGMP_V3_UNSOLICITED_NS : IGMP_V2_UNSOLICITED_NS;
+            entry->report_at = entry->changes ? now + igmp_random_delay(interval) : 0;
+        } else {
+            // Answer to a query
+            entry->report_at = 0;
+            if (!entry->users) {
+                len = 0;
+            } else if (version == 3) {
+                len = igmp_build_v3(frame, iface, IGMPV3_MODE_IS_EXCLUDE, entry->group);
+                igmp_stats.reports_sent++;
+            } else {
+                entry->last_reporter = true;
+                len = igmp_build_v2(frame, iface, version == 1 ? IGMP_V1_REPORT : IGMP_V2_REPORT, entry->group);
+                igmp_stats.reports_sent++;
+            }
+        }
+
+        // A group left is forgotten once its leave is announced
+        if (!entry->users && !entry->changes) {
+            memset(entry, 0, sizeof(*entry));
+        }
+        if (len) {
+            return len;
+        }
+    }
+    return 0;
+}
+
+void orion_igmp_timers(void)
+{
+    uint8_t frame[IGMP_FRAME_MAX];
+
+    // One report per pass, sent without the lock held
+    for (;;) {
+        uint64_t now = wallclock_monotonic_ns();
+        orion_net_driver_t *driver = NULL;
+        size_t len = 0;
+
+        spinlock_acquire(&igmp_lock);
+        for (int i = 0; i < ORION_IGMP_MAX_IFACES && !len; i++) {
+            if (igmp_ifaces[i].driver) {
+                len = igmp_next_due(&igmp_ifaces[i], frame, now);
+                driver = igmp_ifaces[i].driver;
+            }
+        }
+        spinlock_release(&igmp_lock);
+
+        if (!len) {
+            return;
+        }
+        if (driver->transmit && driver->transmit(driver, frame, len) != 0) {
+            klog_debug(KLOG_CAT_KERNEL, "IGMP: report not sent on %s", driver->name);
+        }
+    }
+}
+
+/* ============================================================================
+ * Interfaces and Groups
+ * ============================================================================ */
+
+int orion_igmp_attach(orion_net_driver_t *driver, uint32_t ifaddr, const uint8_t mac[6])
+{
+    if (!driver || !mac || ifaddr == 0) {
+        return -1;
+    }
+
+    spinlock_acquire(&igmp_lock);
+    igmp_iface_t *slot = NULL;
+    for (int i = 0; i < ORION_IGMP_MAX_IFACES; i++) {
+        igmp_iface_t *iface = &igmp_ifaces[i];
+        if (iface->driver == driver || iface->ifaddr == ifaddr) {
+            slot = NULL;
+            break;
+        }
+        if (!iface->driver && !slot) {
+            slot = iface;
+        }
+    }
+    if (!slot) {
+        spinlock_release(&igmp_lock);
+        return -1;
+    }
+    memset(slot, 0, sizeof(*slot));
+    slot->driver = driver;
+    slot->ifaddr = ifaddr;
+    memcpy(slot->mac, mac, 6);
+    spinlock_release(&igmp_lock);
+
+    igmp_program_filter(ifaddr);
+    klog_info(KLOG_CAT_KERNEL, "IGMP enabled on %s", driver->name);
+    return 0;
+}
+
+void orion_igmp_detach(orion_net_driver_t *driver)
+{
+    spinlock_acquire(&igmp_lock);
+    for (int i = 0; i < ORION_IGMP_MAX_IFACES; i++) {
+        if (igmp_ifaces[i].driver == driver) {
+            memset(&igmp_ifaces[i], 0, sizeof(igmp_ifaces[i]));
+        }
+    }
+    spinlock_release(&igmp_lock);
+}
+
+int orion_igmp_join(uint32_t ifaddr, uint32_t group)
+{
+    if (!ORION_IN_MULTICAST(group)) {
+        return -1;
+    }
+
+    spinlock_acquire(&igmp_lock);
+    igmp_iface_t *iface = igmp_find_iface(ifaddr);
+    if (!iface) {
+        spinlock_release(&igmp_lock);
+        return -1;
+    }
+    if (group == ORION_IGMP_ALL_HOSTS) {
+        // Always joined
+        spinlock_release(&igmp_lock);
+        return 0;
+    }
+
+    igmp_group_t *entry = igmp_find_group(iface, group);
+    if (entry && entry->users) {
+        entry->users++;
+        spinlock_release(&igmp_lock);
+        return 0;
+    }
+    if (!entry) {
+        entry = igmp_find_group(iface, 0);
+        if (!entry) {
+            spinlock_release(&igmp_lock);
+            klog_warning(KLOG_CAT_KERNEL, "IGMP: group table full");
+            return -1;
+        }
+        entry->group = group;
+    }
+    // Joined again while its leave was being announced: a fresh state change
+    entry->users = 1;
+    entry->changes = ORION_IGMP_ROBUSTNESS;
+    entry->last_reporter = false;
+    entry->report_at = wallclock_monotonic_ns();
+    ifaddr = iface->ifaddr;
+    spinlock_release(&igmp_lock);
+
+    igmp_program_filter(ifaddr);
+    orion_igmp_timers();
+    return 0;
+}
+
+int orion_igmp_leave(uint32_t ifaddr, uint32_t group)
+{
+    if (group == ORION_IGMP_ALL_HOSTS) {
+        return 0;
+    }
+
+    spinlock_acquire(&igmp_lock);
+    igmp_iface_t *iface = igmp_find_iface(ifaddr);
+    igmp_group_t *entry = iface ? igmp_find_group(iface, group) : NULL;
+    if (!entry || !entry->users) {
+        spinlock_release(&igmp_lock);
+        return -1;
+    }
+    if (--entry->users) {
+        spinlock_release(&igmp_lock);
+        return 0;
+    }
+    int version = igmp_version(iface, wallclock_monotonic_ns());
+    entry->changes = version == 3 ? ORION_IGMP_ROBUSTNESS : 1;
+    entry->report_at = wallclock_monotonic_ns();
+    ifaddr = iface->ifaddr;
+    spinlock_release(&igmp_lock);
+
+    igmp_program_filter(ifaddr);
+    orion_igmp_timers();
+    return 0;
+}
+
+bool orion_igmp_is_member(uint32_t group)
+{
+    if (group == ORION_IGMP_ALL_HOSTS) {
+        return true;
+    }
+
+    bool member = false;
+    spinlock_acquire(&igmp_lock);
+    for (int i = 0; i < ORION_IGMP_MAX_IFACES && !member; i++) {
+        if (igmp_ifaces[i].driver) {
+            igmp_group_t *entry = igmp_find_group(&igmp_ifaces[i], group);
+            member = entry && entry->users;
+        }
+    }
+    spinlock_release(&igmp_lock);
+    return member;
+}
+
+/* ============================================================================
+ * Input
+ * ============================================================================ */
+
+// Maximum Response Code of an IGMPv3 query, in tenths of a second
+static uint32_t igmp_v3_max_resp(uint8_t code)
+{
+    if (code < 128) {
+        return code;
+    }
+    uint32_t mantissa = code & 0x0F;
+    uint32_t exponent = (code >> 4) & 0x07;
+    return (mantissa | 0x10) << (exponent + 3);
+}
+
+// Called with igmp_lock held
+static void igmp_query(igmp_iface_t *iface, const uint8_t *message, size_t len, uint64_t now)
+{
+    int before = igmp_version(iface, now);
+    uint32_t max_resp;
+    if (len == IGMP_V2_LEN && message[1] == 0) {
+        iface->v1_querier_until = now + IGMP_OLDER_QUERIER_NS;
+        max_resp = IGMP_V1_MAX_RESP;
+    } else if (len == IGMP_V2_LEN) {
+        iface->v2_querier_until = now + IGMP_OLDER_QUERIER_NS;
+        max_resp = message[1];
+    } else if (len >= IGMP_V3_QUERY_LEN) {
+        max_resp = igmp_v3_max_resp(message[1]);
+    } else {
+        igmp_stats.bad_messages++;
+        return;
+    }
+    igmp_stats.queries_received++;
+
+    int version = igmp_version(iface, now);
+    if (version != before) {
+        // Pending reports were of the previous version
+        iface->general_report_at = 0;
+        for (int i = 0; i < ORION_IGMP_MAX_GROUPS; i++) {
+            igmp_group_t *entry = &iface->groups[i];
+            entry->changes = 0;
+            entry->report_at = 0;
+            if (!entry->users) {
+                memset(entry, 0, sizeof(*entry));
+            }
+        }
+    }
+
+    uint64_t max_ns = (uint64_t)max_resp * IGMP_NS_PER_UNIT;
+    uint32_t group = get_be32(message + 4);
+    if (group == 0 && version == 3) {
+        igmp_schedule(&iface->general_report_at, now, max_ns);
+        return;
+    }
+    // IGMPv1/v2 general queries and group-specific queries are answered per group;
+    // source-specific queries as group-specific ones, no source being excluded
+    for (int i = 0; i < ORION_IGMP_MAX_GROUPS; i++) {
+        igmp_group_t *entry = &iface->groups[i];
+        if (entry->users && (group == 0 || entry->group == group)) {
+            igmp_schedule(&entry->report_at, now, max_ns);
+        }
+    }
+}
+
+int orion_igmp_input(uint32_t src_ip, uint32_t dst_ip, const void *message, size_t len)
+{
+    (void)dst_ip;
+    const uint8_t *igmp = message;
+    if (!igmp || len < IGMP_V2_LEN || igmp_checksum(igmp, len) != 0) {
+        spinlock_acquire(&igmp_lock);
+        igmp_stats.bad_messages++;
+        spinlock_release(&igmp_lock);
+        return -1;
+    }
+
+    uint64_t now = wallclock_monotonic_ns();
+    spinlock_acquire(&igmp_lock);
+    for (int i = 0; i < ORION_IGMP_MAX_IFACES; i++) {
+        igmp_iface_t *iface = &igmp_ifaces[i];
+        // Our own reports come back through multicast loopback
+        if (!iface->driver || iface->ifaddr == src_ip) {
+            continue;
+        }
+
+        switch (igmp[0]) {
+        case IGMP_QUERY:
+            igmp_query(iface, igmp, len, now);
+            break;
+
+        case IGMP_V1_REPORT:
+        case IGMP_V2_REPORT: {
+            igmp_stats.reports_received++;
+            // Another member answered the querier for the group: ours is redundant
+            igmp_group_t *entry = igmp_find_group(iface, get_be32(igmp + 4));
+            if (entry && entry->users && igmp_version(iface, now) < 3) {
+                if (entry->report_at && !entry->changes) {
+                    entry->report_at = 0;
+                    igmp_stats.reports_suppressed++;
+                }
+                entry->last_reporter = false;
+            }
+            break;
+        }
+
+        default:
+            // IGMPv3 reports and leaves are for routers
+            break;
+        }
+    }
+    spinlock_release(&igmp_lock);
+    return 0;
+}
+
+void orion_igmp_get_stats(orion_igmp_stats_t *stats)
+{
+    if (!stats) {
+        return;
+    }
+    spinlock_acquire(&igmp_lock);
+    *stats = igmp_stats;
+    spinlock_release(&igmp_lock);
+}
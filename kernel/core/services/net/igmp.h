@@ -0,0 +1,128 @@
+/*
+ * Orion Operating System - IGMP Multicast Group Membership
+ *
+ * IPv4 multicast groups joined by the host on each interface, announced
+ * to multicast routers with IGMP. Sockets join and leave through the UDP
+ * layer; a group stays joined while at least one socket holds it, and the
+ * driver's multicast filter is reprogrammed whenever the set of groups
+ * changes.
+ *
+ * IGMPv3 (RFC 3376) is spoken by default, with every group in EXCLUDE
+ * mode and no source list. An interface falls back to IGMPv2 (RFC 2236)
+ * or IGMPv1 while a querier of that version is heard, for the Older
+ * Version Querier Present Timeout. Joins and leaves are announced with
+ * unsolicited reports sent Robustness Variable times; queries are answered
+ * after a random delay up to the advertised maximum response time, and in
+ * IGMPv1/v2 a report heard from another host cancels ours. The all-hosts
+ * group 224.0.0.1 is always joined and never reported.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_IGMP_H
+#define ORION_NET_IGMP_H
+
+#include <orion/types.h>
+#include "network_architecture.h"
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_IP_PROTOCOL_IGMP 2
+
+#define ORION_IGMP_MAX_IFACES 4   // Interfaces with multicast enabled
+#define ORION_IGMP_MAX_GROUPS 32  // Groups joined per interface
+#define ORION_IGMP_ROBUSTNESS 2   // Unsolicited reports per state change
+
+#define ORION_IGMP_ALL_HOSTS 0xE0000001   // 224.0.0.1
+#define ORION_IGMP_ALL_ROUTERS 0xE0000002 // 224.0.0.2, IGMPv2 leaves
+#define ORION_IGMPV3_ROUTERS 0xE0000016   // 224.0.0.22, IGMPv3 reports
+
+#define ORION_IN_MULTICAST(addr) (((addr) & 0xF0000000U) == 0xE0000000U)
+
+    typedef struct
+    {
+        uint64_t reports_sent;       // Membership reports, solicited or not
+        uint64_t leaves_sent;        // IGMPv2 leaves and IGMPv3 TO_IN({}) reports
+        uint64_t queries_received;   // General and group-specific queries
+        uint64_t reports_received;   // Reports of other hosts
+        uint64_t reports_suppressed; // Our IGMPv1/v2 reports cancelled by another host's
+        uint64_t bad_messages;       // Short or with a wrong checksum
+    } orion_igmp_stats_t;
+
+    /**
+     * @brief Map an IPv4 multicast group to its Ethernet address (01:00:5e + low 23 bits)
+     * @param group Group address
+     * @param mac Ethernet address (output)
+     */
+    void orion_ip_multicast_mac(uint32_t group, uint8_t mac[6]);
+
+    /**
+     * @brief Enable multicast on an interface
+     * @param driver Driver of the interface; its multicast filter is reprogrammed on every change
+     * @param ifaddr IPv4 address of the interface, source of the reports
+     * @param mac Ethernet address of the interface
+     * @return 0 on success, negative value on error
+     */
+    int orion_igmp_attach(orion_net_driver_t *driver, uint32_t ifaddr, const uint8_t mac[6]);
+
+    /**
+     * @brief Disable multicast on an interface, leaving its groups silently
+     * @param driver Driver passed to orion_igmp_attach
+     */
+    void orion_igmp_detach(orion_net_driver_t *driver);
+
+    /**
+     * @brief Take a reference on a group, joining it on the first
+     * @param ifaddr Interface address, 0 for the first interface attached
+     * @param group Group address
+     * @return 0 on success, negative value on error
+     */
+    int orion_igmp_join(uint32_t ifaddr, uint32_t group);
+
+    /**
+     * @brief Drop a reference on a group, leaving it on the last
+     * @param ifaddr Interface address given to orion_igmp_join
+     * @param group Group address
+     * @return 0 on success, negative value if the group was not joined
+     */
+    int orion_igmp_leave(uint32_t ifaddr, uint32_t group);
+
+    /**
+     * @brief Whether the host listens to a group
+     * @param group Group address
+     * @return true if joined on any interface
+     */
+    bool orion_igmp_is_member(uint32_t group);
+
+    /**
+     * @brief Process a received IGMP message
+     * @param src_ip Source IP address
+     * @param dst_ip Destination IP address
+     * @param message IGMP message
+     * @param len Message length
+     * @return 0 on success, negative value if the message was invalid
+     */
+    int orion_igmp_input(uint32_t src_ip, uint32_t dst_ip, const void *message, size_t len);
+
+    /**
+     * @brief Send the reports that are due; called periodically by the network server
+     */
+    void orion_igmp_timers(void);
+
+    /**
+     * @brief IGMP counters
+     */
+    void orion_igmp_get_stats(orion_igmp_stats_t *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_IGMP_H
@@ -0,0 +1,370 @@
+/*
+ * Orion Operating System - Socket I/O Rings Implementation
+ *
+ * Entries are carried out through the socket request handler, so ring
+ * operations get exactly the ownership checks and semantics of the
+ * equivalent SEND, RECV and CLOSE requests. The region layout is the one
+ * of lib/orion_ring/src/ring.rs. The server keeps its own copy of the
+ * indices it owns and bounds every index and buffer read from the
+ * region, which the client can rewrite at any time.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "io_ring.h"
+#include "socket_ipc.h"
+#include <orion/mm.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define RING_STATUS_OK 0
+#define RING_STATUS_ENOENT -2
+#define RING_STATUS_EIO -5
+#define RING_STATUS_ENOMEM -12
+#define RING_STATUS_EFAULT -14
+#define RING_STATUS_EBUSY -16
+#define RING_STATUS_EINVAL -22
+#define RING_STATUS_ENOSPC -28
+#define RING_STATUS_EOPNOTSUPP -95
+
+// Region layout
+#define RING_MAGIC 0x474E524FU // "ORNG"
+#define RING_VERSION 1
+#define RING_HEADER_SIZE 0x40
+#define RING_SQE_SIZE 40
+#define RING_CQE_SIZE 16
+#define RING_DATA_ALIGN 64
+
+#define RING_MAGIC_FIELD 0x00
+#define RING_VERSION_FIELD 0x04
+#define RING_SQ_ENTRIES 0x08
+#define RING_CQ_ENTRIES 0x0C
+#define RING_SQ_HEAD 0x10
+#define RING_SQ_TAIL 0x14
+#define RING_CQ_HEAD 0x18
+#define RING_CQ_TAIL 0x1C
+
+// Socket request and reply scratch space for one entry
+#define RING_SCRATCH_SIZE (12 + ORION_SOCKET_MAX_TRANSFER)
+
+typedef struct {
+    uint8_t *base;
+    size_t size;
+    uint64_t owner;
+    uint32_t sq_entries;
+    uint32_t cq_entries;
+    size_t cq_offset;
+    size_t data_offset;
+    uint32_t sq_head; // Private copies of the indices the server owns
+    uint32_t cq_tail;
+    bool busy;        // An ENTER is running the ring
+} io_ring_t;
+
+static io_ring_t rings[ORION_IO_RING_MAX_RINGS];
+static spinlock_t ring_lock = SPINLOCK_INITIALIZER;
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static uint64_t get_u64(const uint8_t *p)
+{
+    return (uint64_t)get_u32(p) | ((uint64_t)get_u32(p + 4) << 32);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static size_t ring_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+/* ============================================================================
+ * Shared Region Access
+ * ============================================================================ */
+
+static uint32_t *ring_field(const io_ring_t *ring, size_t offset)
+{
+    return (uint32_t *)(ring->base + offset);
+}
+
+static uint32_t ring_load(const io_ring_t *ring, size_t offset)
+{
+    return __atomic_load_n(ring_field(ring, offset), __ATOMIC_ACQUIRE);
+}
+
+static void ring_store(const io_ring_t *ring, size_t offset, uint32_t value)
+{
+    __atomic_store_n(ring_field(ring, offset), value, __ATOMIC_RELEASE);
+}
+
+static bool ring_valid_entries(uint32_t entries)
+{
+    return entries != 0 && (entries & (entries - 1)) == 0 && entries <= ORION_IO_RING_MAX_ENTRIES;
+}
+
+// Data area offset of a buffer, or 0 if it does not fit
+static size_t ring_data(const io_ring_t *ring, uint64_t buffer, size_t length)
+{
+    size_t data_size = ring->size - ring->data_offset;
+    if (buffer > data_size || length > data_size - buffer) {
+        return 0;
+    }
+    return ring->data_offset + (size_t)buffer;
+}
+
+/* ============================================================================
+ * Entry Execution
+ * ============================================================================ */
+
+// Run one entry through the socket request handler
+static int32_t ring_execute(io_ring_t *ring, uint8_t opcode, uint32_t socket, uint64_t buffer, uint32_t length,
+                            uint8_t *request, uint8_t *reply)
+{
+    size_t request_len = 8;
+    size_t len = length < ORION_SOCKET_MAX_TRANSFER ? length : ORION_SOCKET_MAX_TRANSFER;
+    size_t data = 0;
+
+    put_u32(request + 4, socket);
+    switch (opcode) {
+    case ORION_IO_RING_NOP:
+        return RING_STATUS_OK;
+
+    case ORION_IO_RING_SEND:
+        data = ring_data(ring, buffer, len);
+        if (!data) {
+            return RING_STATUS_EFAULT;
+        }
+        put_u32(request, ORION_SOCKET_OP_SEND);
+        memcpy(request + 8, ring->base + data, len);
+        request_len += len;
+        break;
+
+    case ORION_IO_RING_RECV:
+        data = ring_data(ring, buffer, len);
+        if (!data) {
+            return RING_STATUS_EFAULT;
+        }
+        put_u32(request, ORION_SOCKET_OP_RECV);
+        put_u32(request + 8, (uint32_t)len);
+        request_len += 4;
+        break;
+
+    case ORION_IO_RING_CLOSE:
+        put_u32(request, ORION_SOCKET_OP_CLOSE);
+        break;
+
+    default:
+        return RING_STATUS_EOPNOTSUPP;
+    }
+
+    size_t reply_len = orion_socket_ipc_handle(ring->owner, request, request_len, reply, RING_SCRATCH_SIZE);
+    if (reply_len < 4) {
+        return RING_STATUS_EIO;
+    }
+    int32_t status = (int32_t)get_u32(reply);
+    if (status < 0) {
+        return status;
+    }
+
+    if (opcode == ORION_IO_RING_SEND) {
+        return reply_len >= 8 ? (int32_t)get_u32(reply + 4) : RING_STATUS_EIO;
+    }
+    if (opcode == ORION_IO_RING_RECV) {
+        size_t received = reply_len - 4;
+        if (received > len) {
+            return RING_STATUS_EIO;
+        }
+        memcpy(ring->base + data, reply + 4, received);
+        return (int32_t)received;
+    }
+    return RING_STATUS_OK;
+}
+
+// Consume up to max entries (0 for all); entries stay queued while the
+// completion queue has no room for their answer
+static int ring_process(io_ring_t *ring, uint32_t max, uint32_t *consumed)
+{
+    uint8_t *request = kmalloc(RING_SCRATCH_SIZE);
+    uint8_t *reply = kmalloc(RING_SCRATCH_SIZE);
+    int status = RING_STATUS_OK;
+
+    *consumed = 0;
+    if (!request || !reply) {
+        status = RING_STATUS_ENOMEM;
+        goto out;
+    }
+
+    while (max == 0 || *consumed < max) {
+        uint32_t queued = ring_load(ring, RING_SQ_TAIL) - ring->sq_head;
+        uint32_t completions = ring->cq_tail - ring_load(ring, RING_CQ_HEAD);
+        if (queued > ring->sq_entries || completions > ring->cq_entries) {
+            status = RING_STATUS_EIO;
+            break;
+        }
+        if (queued == 0 || completions == ring->cq_entries) {
+            break;
+        }
+
+        uint8_t sqe[RING_SQE_SIZE];
+        size_t slot = ring->sq_head & (ring->sq_entries - 1);
+        memcpy(sqe, ring->base + RING_HEADER_SIZE + slot * RING_SQE_SIZE, RING_SQE_SIZE);
+        ring->sq_head++;
+        ring_store(ring, RING_SQ_HEAD, ring->sq_head);
+
+        int32_t result = ring_execute(ring, sqe[0], get_u32(sqe + 4), get_u64(sqe + 16), get_u32(sqe + 24),
+                                      request, reply);
+
+        uint8_t cqe[RING_CQE_SIZE];
+        memcpy(cqe, sqe + 32, 8); // user_data
+        put_u32(cqe + 8, (uint32_t)result);
+        put_u32(cqe + 12, 0);
+        slot = ring->cq_tail & (ring->cq_entries - 1);
+        memcpy(ring->base + ring->cq_offset + slot * RING_CQE_SIZE, cqe, RING_CQE_SIZE);
+        ring->cq_tail++;
+        ring_store(ring, RING_CQ_TAIL, ring->cq_tail);
+        (*consumed)++;
+    }
+
+out:
+    kfree(request);
+    kfree(reply);
+    return status;
+}
+
+/* ============================================================================
+ * Registration
+ * ============================================================================ */
+
+int orion_io_ring_register(uint64_t owner, void *base, size_t size, uint32_t *id)
+{
+    if (!base || !id || ((uintptr_t)base & 7) || size < RING_HEADER_SIZE) {
+        return RING_STATUS_EINVAL;
+    }
+
+    io_ring_t ring = {0};
+    ring.base = base;
+    ring.size = size;
+    ring.owner = owner;
+    if (ring_load(&ring, RING_MAGIC_FIELD) != RING_MAGIC || ring_load(&ring, RING_VERSION_FIELD) != RING_VERSION) {
+        return RING_STATUS_EINVAL;
+    }
+    ring.sq_entries = ring_load(&ring, RING_SQ_ENTRIES);
+    ring.cq_entries = ring_load(&ring, RING_CQ_ENTRIES);
+    if (!ring_valid_entries(ring.sq_entries) || !ring_valid_entries(ring.cq_entries) ||
+        ring.cq_entries < ring.sq_entries) {
+        return RING_STATUS_EINVAL;
+    }
+    ring.cq_offset = RING_HEADER_SIZE + (size_t)ring.sq_entries * RING_SQE_SIZE;
+    ring.data_offset = (ring.cq_offset + (size_t)ring.cq_entries * RING_CQE_SIZE + RING_DATA_ALIGN - 1) &
+                       ~(size_t)(RING_DATA_ALIGN - 1);
+    if (size < ring.data_offset) {
+        return RING_STATUS_EINVAL;
+    }
+    ring.sq_head = ring_load(&ring, RING_SQ_HEAD);
+    ring.cq_tail = ring_load(&ring, RING_CQ_TAIL);
+
+    int status = RING_STATUS_ENOSPC;
+    uint32_t owned = 0;
+    spinlock_acquire(&ring_lock);
+    for (uint32_t i = 0; i < ORION_IO_RING_MAX_RINGS; i++) {
+        if (rings[i].base && rings[i].owner == owner) {
+            owned++;
+        }
+    }
+    for (uint32_t i = 0; i < ORION_IO_RING_MAX_RINGS && owned < ORION_IO_RING_MAX_PER_PROCESS; i++) {
+        if (!rings[i].base) {
+            rings[i] = ring;
+            *id = i + 1;
+            status = RING_STATUS_OK;
+            break;
+        }
+    }
+    spinlock_release(&ring_lock);
+    return status;
+}
+
+void *orion_io_ring_unregister(uint64_t owner, uint32_t id)
+{
+    void *base = NULL;
+
+    if (id == 0 || id > ORION_IO_RING_MAX_RINGS) {
+        return NULL;
+    }
+    spinlock_acquire(&ring_lock);
+    io_ring_t *ring = &rings[id - 1];
+    if (ring->base && ring->owner == owner && !ring->busy) {
+        base = ring->base;
+        memset(ring, 0, sizeof(*ring));
+    }
+    spinlock_release(&ring_lock);
+    return base;
+}
+
+size_t orion_io_ring_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len,
+                                uint8_t *reply, size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 8) {
+        return 0;
+    }
+    if (request_len < 12 || get_u32(request) != ORION_IO_RING_OP_ENTER) {
+        return ring_reply(reply, RING_STATUS_EINVAL, 0);
+    }
+
+    uint32_t id = get_u32(request + 4);
+    uint32_t to_submit = get_u32(request + 8);
+    if (id == 0 || id > ORION_IO_RING_MAX_RINGS) {
+        return ring_reply(reply, RING_STATUS_ENOENT, 0);
+    }
+
+    // The busy flag keeps the ring registered while it runs unlocked
+    int status = RING_STATUS_OK;
+    io_ring_t *ring = &rings[id - 1];
+    spinlock_acquire(&ring_lock);
+    if (!ring->base || ring->owner != sender) {
+        status = RING_STATUS_ENOENT;
+    } else if (ring->busy) {
+        status = RING_STATUS_EBUSY;
+    } else {
+        ring->busy = true;
+    }
+    spinlock_release(&ring_lock);
+    if (status != RING_STATUS_OK) {
+        return ring_reply(reply, status, 0);
+    }
+
+    uint32_t consumed = 0;
+    status = ring_process(ring, to_submit, &consumed);
+
+    spinlock_acquire(&ring_lock);
+    ring->busy = false;
+    spinlock_release(&ring_lock);
+
+    if (status != RING_STATUS_OK && consumed == 0) {
+        return ring_reply(reply, status, 0);
+    }
+    put_u32(reply + 4, consumed);
+    return ring_reply(reply, RING_STATUS_OK, 4);
+}
+
+void orion_io_ring_release(uint64_t owner, void (*unmap)(void *base))
+{
+    for (uint32_t i = 0; i < ORION_IO_RING_MAX_RINGS; i++) {
+        void *base = orion_io_ring_unregister(owner, i + 1);
+        if (base && unmap) {
+            unmap(base);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * Orion Operating System - Socket I/O Rings
+ *
+ * Submission/completion rings for socket operations, the network server
+ * side of lib/orion_ring. An application queues SEND, RECV and CLOSE
+ * entries on its sockets in a shared memory region and hands them over
+ * with one ENTER doorbell. Same framing as socket_ipc.h:
+ *
+ *   REGISTER    (shared memory capability) -> ring:u32
+ *   ENTER       ring:u32 to_submit:u32     -> consumed:u32
+ *   UNREGISTER  ring:u32                   -> (empty)
+ *
+ * REGISTER and UNREGISTER map and unmap the region, so the message loop
+ * handles them with orion_io_ring_register and orion_io_ring_unregister;
+ * ENTER goes through orion_io_ring_ipc_handle. Every consumed entry has
+ * its completion posted before ENTER replies. RECV on an empty socket
+ * completes with -EAGAIN rather than waiting.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_IO_RING_H
+#define ORION_IO_RING_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_IO_RING_OP_REGISTER 0x100
+#define ORION_IO_RING_OP_ENTER 0x101
+#define ORION_IO_RING_OP_UNREGISTER 0x102
+
+#define ORION_IO_RING_MAX_RINGS 64       // Rings across all clients
+#define ORION_IO_RING_MAX_PER_PROCESS 4  // Rings one client may register
+#define ORION_IO_RING_MAX_ENTRIES 4096   // Largest queue accepted
+
+// Submission opcodes handled by the network server
+#define ORION_IO_RING_NOP 0
+#define ORION_IO_RING_CLOSE 4
+#define ORION_IO_RING_SEND 5
+#define ORION_IO_RING_RECV 6
+
+    /**
+     * @brief Take over a ring region mapped for a client
+     * @param owner Process that registered the ring
+     * @param base Address the region is mapped at (8-byte aligned)
+     * @param size Region size
+     * @param id Ring identifier (output)
+     * @return 0 or a negative errno
+     */
+    int orion_io_ring_register(uint64_t owner, void *base, size_t size, uint32_t *id);
+
+    /**
+     * @brief Forget a ring
+     * @param owner Process that registered the ring
+     * @param id Ring identifier
+     * @return Region to unmap, or NULL if the ring is unknown or busy
+     */
+    void *orion_io_ring_unregister(uint64_t owner, uint32_t id);
+
+    /**
+     * @brief Handle one ENTER request
+     * @param sender Process that sent the request
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_io_ring_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len,
+                                    uint8_t *reply, size_t reply_capacity);
+
+    /**
+     * @brief Drop every ring of an exiting process
+     * @param owner Process identifier
+     * @param unmap Called with each region to unmap
+     */
+    void orion_io_ring_release(uint64_t owner, void (*unmap)(void *base));
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_IO_RING_H
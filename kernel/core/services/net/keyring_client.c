@@ -0,0 +1,178 @@
+/*
+ * Orion Operating System - Keyring Client
+ *
+ * Request/reply calls to the keyring service over IPC ports. Calls are
+ * serialized: the network server keeps a single reply port.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "keyring_client.h"
+#include <orion/klog.h>
+#include <orion/mm.h>
+#include <orion/string.h>
+#include <orion/spinlock.h>
+#include <string.h>
+
+extern int ipc_send_message(or_cap_t port, const void *data, size_t size, uint64_t timeout_ns);
+extern int ipc_recv_message(or_cap_t port, void *buffer, size_t size, uint64_t timeout_ns);
+
+// The keyring answers from memory; anything slower means it is gone
+#define KEYRING_TIMEOUT_NS 1000000000ULL
+
+// Room for a certificate whose signature is checked
+#define KEYRING_REQUEST_MAX 4096
+#define KEYRING_REPLY_MAX (4 + ORION_KEYRING_MAX_PAYLOAD)
+
+static struct {
+    bool connected;
+    uint64_t keyring_port;
+    uint64_t reply_port;
+    uint8_t request[KEYRING_REQUEST_MAX];
+    uint8_t reply[KEYRING_REPLY_MAX];
+} keyring_client = {0};
+
+static spinlock_t keyring_lock = SPINLOCK_INITIALIZER;
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static void put_u64(uint8_t *p, uint64_t v)
+{
+    for (int i = 0; i < 8; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+/*
+ * Send the request staged in keyring_client.request and wait for the
+ * reply. Returns the payload length past the status word, or the negative
+ * status. Must be called with keyring_lock held.
+ */
+static int keyring_call(size_t request_len)
+{
+    if (!keyring_client.connected) {
+        return -1;
+    }
+
+    int result = ipc_send_message(keyring_client.keyring_port, keyring_client.request,
+                                  request_len, KEYRING_TIMEOUT_NS);
+    memset(keyring_client.request, 0, request_len);
+    if (result < 0) {
+        klog_error(KLOG_CAT_KERNEL, "Keyring request failed: %d", result);
+        return result;
+    }
+
+    int received = ipc_recv_message(keyring_client.reply_port, keyring_client.reply,
+                                    sizeof(keyring_client.reply), KEYRING_TIMEOUT_NS);
+    if (received < 4) {
+        klog_error(KLOG_CAT_KERNEL, "Keyring reply missing: %d", received);
+        return received < 0 ? received : -1;
+    }
+
+    int32_t status = (int32_t)((uint32_t)keyring_client.reply[0] |
+                               ((uint32_t)keyring_client.reply[1] << 8) |
+                               ((uint32_t)keyring_client.reply[2] << 16) |
+                               ((uint32_t)keyring_client.reply[3] << 24));
+    if (status != 0) {
+        return status;
+    }
+    return received - 4;
+}
+
+int orion_keyring_client_init(uint64_t keyring_port, uint64_t reply_port)
+{
+    if (keyring_port == 0 || reply_port == 0) {
+        return -1;
+    }
+
+    spinlock_acquire(&keyring_lock);
+    keyring_client.keyring_port = keyring_port;
+    keyring_client.reply_port = reply_port;
+    keyring_client.connected = true;
+    spinlock_release(&keyring_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "Keyring client connected (port %llu)", (unsigned long long)keyring_port);
+    return 0;
+}
+
+int orion_keyring_read(uint64_t handle, void *buffer, size_t capacity, size_t *length)
+{
+    if (!buffer || !length) {
+        return -1;
+    }
+
+    spinlock_acquire(&keyring_lock);
+    put_u32(keyring_client.request, ORION_KEYRING_OP_READ);
+    put_u64(keyring_client.request + 4, handle);
+
+    int result = keyring_call(12);
+    if (result >= 0) {
+        if ((size_t)result > capacity) {
+            result = -1;
+        } else {
+            memcpy(buffer, keyring_client.reply + 4, result);
+            *length = result;
+            result = 0;
+        }
+    }
+    memset(keyring_client.reply, 0, sizeof(keyring_client.reply));
+    spinlock_release(&keyring_lock);
+
+    return result;
+}
+
+int orion_keyring_sign(uint64_t handle, const void *message, size_t len,
+                       uint8_t signature[ORION_KEYRING_SIGNATURE_SIZE])
+{
+    if (!message || !signature || len > KEYRING_REQUEST_MAX - 12) {
+        return -1;
+    }
+
+    spinlock_acquire(&keyring_lock);
+    put_u32(keyring_client.request, ORION_KEYRING_OP_SIGN);
+    put_u64(keyring_client.request + 4, handle);
+    memcpy(keyring_client.request + 12, message, len);
+
+    int result = keyring_call(12 + len);
+    if (result >= 0) {
+        if (result != ORION_KEYRING_SIGNATURE_SIZE) {
+            result = -1;
+        } else {
+            memcpy(signature, keyring_client.reply + 4, ORION_KEYRING_SIGNATURE_SIZE);
+            result = 0;
+        }
+    }
+    spinlock_release(&keyring_lock);
+
+    return result;
+}
+
+int orion_keyring_verify(const uint8_t public_key[ORION_KEYRING_PUBLIC_KEY_SIZE], const void *message,
+                         size_t len, const uint8_t signature[ORION_KEYRING_SIGNATURE_SIZE])
+{
+    size_t header = 4 + ORION_KEYRING_PUBLIC_KEY_SIZE + ORION_KEYRING_SIGNATURE_SIZE;
+
+    if (!public_key || !message || !signature || len > KEYRING_REQUEST_MAX - header) {
+        return -1;
+    }
+
+    spinlock_acquire(&keyring_lock);
+    put_u32(keyring_client.request, ORION_KEYRING_OP_VERIFY);
+    memcpy(keyring_client.request + 4, public_key, ORION_KEYRING_PUBLIC_KEY_SIZE);
+    memcpy(keyring_client.request + 4 + ORION_KEYRING_PUBLIC_KEY_SIZE, signature, ORION_KEYRING_SIGNATURE_SIZE);
+    memcpy(keyring_client.request + header, message, len);
+
+    int result = keyring_call(header + len);
+    spinlock_release(&keyring_lock);
+
+    return result < 0 ? result : 0;
+}
@@ -0,0 +1,81 @@
+/*
+ * Orion Operating System - Keyring Client
+ *
+ * Minimal client for the keyring service used by the network server: it
+ * reads certificates and asks the keyring to sign with private keys that
+ * never leave it. The wire format is the one of
+ * services/keyring/src/protocol.rs.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_KEYRING_CLIENT_H
+#define ORION_KEYRING_CLIENT_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Keyring opcodes
+#define ORION_KEYRING_OP_READ 3
+#define ORION_KEYRING_OP_SIGN 9
+#define ORION_KEYRING_OP_VERIFY 10
+
+// Largest key payload the keyring stores (KEY_SLOT_SIZE)
+#define ORION_KEYRING_MAX_PAYLOAD 4096
+
+#define ORION_KEYRING_SIGNATURE_SIZE 64
+#define ORION_KEYRING_PUBLIC_KEY_SIZE 32
+
+    /**
+     * @brief Connect the client to the keyring
+     * @param keyring_port Port capability of the keyring service
+     * @param reply_port Port owned by the network server receiving replies
+     * @return 0 on success, negative value on error
+     */
+    int orion_keyring_client_init(uint64_t keyring_port, uint64_t reply_port);
+
+    /**
+     * @brief Read a readable key (e.g. a certificate chain)
+     * @param handle Keyring handle
+     * @param buffer Destination buffer
+     * @param capacity Destination capacity
+     * @param length Receives the payload length
+     * @return 0 on success, negative errno from the keyring on error
+     */
+    int orion_keyring_read(uint64_t handle, void *buffer, size_t capacity, size_t *length);
+
+    /**
+     * @brief Sign a message with a private key held by the keyring
+     * @param handle Keyring handle the caller holds USE on
+     * @param message Message to sign
+     * @param len Message length
+     * @param signature Receives the Ed25519 signature
+     * @return 0 on success, negative errno from the keyring on error
+     */
+    int orion_keyring_sign(uint64_t handle, const void *message, size_t len,
+                           uint8_t signature[ORION_KEYRING_SIGNATURE_SIZE]);
+
+    /**
+     * @brief Check an Ed25519 signature
+     * @param public_key Public key of the signer
+     * @param message Signed message
+     * @param len Message length
+     * @param signature Signature to check
+     * @return 0 if the signature is valid, negative errno otherwise
+     */
+    int orion_keyring_verify(const uint8_t public_key[ORION_KEYRING_PUBLIC_KEY_SIZE], const void *message,
+                             size_t len, const uint8_t signature[ORION_KEYRING_SIGNATURE_SIZE]);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_KEYRING_CLIENT_H
@@ -0,0 +1,199 @@
+/*
+ * Orion Operating System - Network Namespaces Implementation
+ *
+ * Slot i of the table is interface "veth<i>" and address
+ * 10.88.0.(i + 2). The stack cannot remove an interface, so a slot taken
+ * down leaves its interface down, to be configured again by the next
+ * namespace using the slot.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "netns.h"
+#include "network_architecture.h"
+#include "tcp_ip_stack.h"
+#include <orion/klog.h>
+#include <orion/namespace.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define NETNS_MTU 1500
+
+typedef struct
+{
+    uint32_t ns; // Kernel namespace id, 0 for a free slot
+} netns_slot_t;
+
+static netns_slot_t netns_slots[ORION_NETNS_MAX];
+static bool bridge_ready;
+static spinlock_t netns_lock = SPINLOCK_INITIALIZER;
+
+static uint32_t slot_address(int slot)
+{
+    return ORION_NETNS_SUBNET | (uint32_t)(slot + 2);
+}
+
+static void slot_name(int slot, char *name)
+{
+    memset(name, 0, 32);
+    strcpy(name, "veth");
+    // At most two digits with ORION_NETNS_MAX slots
+    if (slot >= 10) {
+        name[4] = (char)('0' + slot / 10);
+        name[5] = (char)('0' + slot % 10);
+    } else {
+        name[4] = (char)('0' + slot);
+    }
+}
+
+static int iface_set(const char *name, orion_net_iface_type_t type, orion_net_iface_state_t state,
+                     const uint8_t mac[6])
+{
+    orion_net_iface_config_t config;
+    orion_net_iface_config_t *current = orion_net_get_interface(name);
+    if (current) {
+        memcpy(&config, current, sizeof(config));
+    } else {
+        memset(&config, 0, sizeof(config));
+        strncpy(config.name, name, sizeof(config.name) - 1);
+        config.mtu = NETNS_MTU;
+    }
+    config.type = type;
+    config.state = state;
+    memcpy(config.mac_addr, mac, 6);
+    return orion_net_configure_interface(name, &config);
+}
+
+// Called with netns_lock held
+static int bridge_setup(void)
+{
+    if (bridge_ready) {
+        return 0;
+    }
+    const uint8_t mac[6] = {0x02, 0x00, 0x0a, 0x58, 0x00, 0x01};
+    if (iface_set(ORION_NETNS_BRIDGE, ORION_NET_IFACE_BRIDGE, ORION_NET_IFACE_UP, mac) != 0 ||
+        orion_ip_add_route(ORION_NETNS_SUBNET, ORION_NETNS_MASK, 0, ORION_NETNS_BRIDGE) != 0) {
+        return -1;
+    }
+    bridge_ready = true;
+    return 0;
+}
+
+// Called with netns_lock held
+static void slot_down(int slot)
+{
+    char name[32];
+    slot_name(slot, name);
+    orion_net_iface_config_t *current = orion_net_get_interface(name);
+    if (current) {
+        iface_set(name, ORION_NET_IFACE_VIRTUAL, ORION_NET_IFACE_DOWN, current->mac_addr);
+    }
+    orion_ip_remove_route(slot_address(slot), 0xFFFFFFFFU);
+    klog_info(KLOG_CAT_KERNEL, "Network namespace %u gone, %s down", netns_slots[slot].ns, name);
+    netns_slots[slot].ns = 0;
+}
+
+// Slot of a namespace, set up on first use. Called with netns_lock held
+static int slot_get(uint32_t ns)
+{
+    int free_slot = -1;
+    for (int i = 0; i < ORION_NETNS_MAX; i++) {
+        if (netns_slots[i].ns == ns) {
+            return i;
+        }
+        if (free_slot < 0 && netns_slots[i].ns == 0) {
+            free_slot = i;
+        }
+    }
+    // A full table may still hold namespaces that are gone
+    for (int i = 0; free_slot < 0 && i < ORION_NETNS_MAX; i++) {
+        if (!ns_in_use(NS_NET, netns_slots[i].ns)) {
+            slot_down(i);
+            free_slot = i;
+        }
+    }
+    if (free_slot < 0 || bridge_setup() != 0) {
+        return -1;
+    }
+
+    char name[32];
+    slot_name(free_slot, name);
+    const uint8_t mac[6] = {0x02, 0x00, 0x0a, 0x58, 0x00, (uint8_t)slot_address(free_slot)};
+    if (iface_set(name, ORION_NET_IFACE_VIRTUAL, ORION_NET_IFACE_UP, mac) != 0 ||
+        orion_ip_add_route(slot_address(free_slot), 0xFFFFFFFFU, 0, name) != 0) {
+        return -1;
+    }
+    netns_slots[free_slot].ns = ns;
+    klog_info(KLOG_CAT_KERNEL, "Network namespace %u on %s, bridged to %s", ns, name, ORION_NETNS_BRIDGE);
+    return free_slot;
+}
+
+uint32_t orion_netns_of(uint64_t pid)
+{
+    ns_info_t info;
+    if (ns_get_info(pid, &info) != 0) {
+        return 0;
+    }
+    return info.net;
+}
+
+int orion_netns_address(uint32_t ns, uint32_t *address)
+{
+    if (ns == 0 || !address) {
+        return -1;
+    }
+    spinlock_acquire(&netns_lock);
+    int slot = slot_get(ns);
+    spinlock_release(&netns_lock);
+    if (slot < 0) {
+        return -1;
+    }
+    *address = slot_address(slot);
+    return 0;
+}
+
+int orion_netns_host_interface(uint32_t ns, char *name)
+{
+    if (ns == 0 || !name) {
+        return -1;
+    }
+    spinlock_acquire(&netns_lock);
+    int slot = slot_get(ns);
+    spinlock_release(&netns_lock);
+    if (slot < 0) {
+        return -1;
+    }
+    slot_name(slot, name);
+    return 0;
+}
+
+bool orion_netns_owns_address(uint32_t address)
+{
+    if ((address & ORION_NETNS_MASK) != ORION_NETNS_SUBNET) {
+        return false;
+    }
+    int slot = (int)(address & 0xFF) - 2;
+    if (slot < 0 || slot >= ORION_NETNS_MAX) {
+        return false;
+    }
+    spinlock_acquire(&netns_lock);
+    bool owned = netns_slots[slot].ns != 0;
+    spinlock_release(&netns_lock);
+    return owned;
+}
+
+void orion_netns_prune(void)
+{
+    spinlock_acquire(&netns_lock);
+    for (int i = 0; i < ORION_NETNS_MAX; i++) {
+        if (netns_slots[i].ns != 0 && !ns_in_use(NS_NET, netns_slots[i].ns)) {
+            slot_down(i);
+        }
+    }
+    spinlock_release(&netns_lock);
+}
@@ -0,0 +1,80 @@
+/*
+ * Orion Operating System - Network Namespaces
+ *
+ * A process in a network namespace of its own (see the kernel's
+ * namespace.h) sees a single interface, "eth0", with its own address. The
+ * interface is one end of a virtual pair whose other end, "vethN", sits on
+ * the host bridge "br0"; the host reaches every namespace over the bridge
+ * subnet, 10.88.0.0/24 with the bridge at 10.88.0.1, and a namespace
+ * gets the first address free from 10.88.0.2 on.
+ *
+ * The server sets a namespace up the first time one of its processes asks
+ * for a socket or an interface, and takes it down once the kernel
+ * namespace has no process left. Sockets of a namespace only bind its
+ * address, a wildcard standing for that address, and connect from it;
+ * host wildcard sockets do not receive what is addressed to a namespace.
+ * Ports of distinct addresses are independent.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_NETNS_H
+#define ORION_NET_NETNS_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_NETNS_MAX 32 // Namespaces with an interface at a time
+
+#define ORION_NETNS_SUBNET 0x0A580000U // 10.88.0.0/24, in host order
+#define ORION_NETNS_MASK 0xFFFFFF00U
+#define ORION_NETNS_BRIDGE_ADDR (ORION_NETNS_SUBNET | 1)
+#define ORION_NETNS_BRIDGE "br0"
+#define ORION_NETNS_IFACE "eth0" // Name of the interface seen in a namespace
+
+    /**
+     * @brief Network namespace of a process
+     * @param pid Global PID, as IPC senders are identified
+     * @return Kernel namespace id, 0 for the host
+     */
+    uint32_t orion_netns_of(uint64_t pid);
+
+    /**
+     * @brief Address of a namespace, setting its interface up if needed
+     * @param ns Namespace id, not 0
+     * @param address Output address in host order
+     * @return 0 on success, -1 when no more namespaces fit or the stack refused the interface
+     */
+    int orion_netns_address(uint32_t ns, uint32_t *address);
+
+    /**
+     * @brief Host side interface of a namespace, set up if needed
+     * @param ns Namespace id, not 0
+     * @param name Output name, 32 bytes
+     * @return 0 on success, -1 as orion_netns_address
+     */
+    int orion_netns_host_interface(uint32_t ns, char *name);
+
+    /**
+     * @brief Whether an address belongs to a namespace rather than the host
+     */
+    bool orion_netns_owns_address(uint32_t address);
+
+    /**
+     * @brief Take down the interfaces of namespaces without processes left
+     */
+    void orion_netns_prune(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_NETNS_H
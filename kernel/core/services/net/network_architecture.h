@@ -269,7 +269,12 @@ extern "C"
 
         // Packet operations
         int (*transmit)(struct orion_net_driver *driver, void *packet, size_t len);
+        // TCP frame larger than the MSS, segmented by the device (ORION_NET_OFFLOAD_TCP_TSO)
+        int (*transmit_tso)(struct orion_net_driver *driver, void *packet, size_t len, uint16_t mss);
         int (*receive)(struct orion_net_driver *driver, void *packet, size_t len);
+        // Multicast groups to accept, count 6-byte addresses, loaded through
+        // FILTER_IOCTL_MULTICAST; NULL addresses accept every group
+        int (*set_multicast)(struct orion_net_driver *driver, const uint8_t *addresses, size_t count);
 
         // Configuration operations
         int (*get_config)(struct orion_net_driver *driver, orion_net_iface_config_t *config);
@@ -349,6 +354,12 @@ extern "C"
      */
     int orion_net_stack_get_stats(void *stats);
 
+    /**
+     * @brief Check the registered drivers and interfaces
+     * @return 0 when the stack is initialized and consistent, -1 otherwise
+     */
+    int orion_net_check_health(void);
+
     /* ============================================================================
      * Network Interface Management
      * ============================================================================ */
@@ -454,6 +465,18 @@ extern "C"
      */
     int orion_net_get_security_status(void *status);
 
+    /* ============================================================================
+     * Network Packet Processing
+     * ============================================================================ */
+
+    /**
+     * @brief Run one received frame through the stack
+     * @param packet Frame, parsed where it lies
+     * @param len Frame length
+     * @return 0 on success, negative value on error
+     */
+    int orion_net_process_packet(void *packet, size_t len);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,466 @@
+/*
+ * Orion Operating System - Generic Receive and Segmentation Offload Implementation
+ *
+ * GRO copies the first segment of a flow into a buffer of its own, as the
+ * receive pool recycles frames as soon as they are processed, and appends
+ * the payload of each following segment behind it. The held packet keeps
+ * the headers of its first segment with the IP length and checksum
+ * rewritten, and PSH set if any merged segment carried it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "offload.h"
+#include "tcp_ip_stack.h"
+#include <orion/klog.h>
+#include <orion/mm.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define ETH_HEADER_LEN 14
+#define ETH_TYPE_IPV4 0x0800
+#define IP_PROTO_TCP 6
+#define IP_MORE_FRAGMENTS 0x2000
+#define IP_OFFSET_MASK 0x1FFF
+#define GRO_BUFFER_SIZE (ETH_HEADER_LEN + ORION_GRO_MAX_SIZE)
+
+// Flags GRO must not merge away; PSH only ends the run
+#define GRO_FLUSH_FLAGS (ORION_TCP_FLAG_FIN | ORION_TCP_FLAG_SYN | ORION_TCP_FLAG_RST | ORION_TCP_FLAG_URG | \
+                         ORION_TCP_FLAG_ECE | ORION_TCP_FLAG_CWR)
+
+// Offsets of a TCP/IPv4 frame
+typedef struct {
+    size_t ip_len;      // IP header
+    size_t tcp_len;     // TCP header
+    size_t payload_off; // From the start of the frame
+    size_t payload_len;
+} tcp_frame_t;
+
+typedef struct {
+    bool active;
+    uint8_t *buf;
+    size_t len;        // Frame held
+    uint32_t next_seq; // Sequence number the next segment must start at
+    uint16_t mss;      // Payload of the first segment
+    uint16_t segs;
+} gro_flow_t;
+
+static struct {
+    bool disabled;
+    gro_flow_t flows[ORION_GRO_MAX_FLOWS];
+    uint32_t evict; // Next flow flushed when all are busy
+    orion_offload_stats_t stats;
+    spinlock_t lock;
+} offload = {.lock = SPINLOCK_INITIALIZER};
+
+/* ============================================================================
+ * Headers and Checksums
+ * ============================================================================ */
+
+static uint16_t get_be16(const uint8_t *p)
+{
+    return (uint16_t)((p[0] << 8) | p[1]);
+}
+
+static void put_be16(uint8_t *p, uint16_t v)
+{
+    p[0] = (uint8_t)(v >> 8);
+    p[1] = (uint8_t)v;
+}
+
+static uint32_t csum_add(uint32_t sum, const uint8_t *data, size_t len)
+{
+    size_t i = 0;
+    for (; i + 1 < len; i += 2) {
+        sum += (uint32_t)((data[i] << 8) | data[i + 1]);
+    }
+    if (i < len) {
+        sum += (uint32_t)data[i] << 8;
+    }
+    return sum;
+}
+
+static uint16_t csum_fold(uint32_t sum)
+{
+    while (sum >> 16) {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    return (uint16_t)~sum;
+}
+
+static orion_ipv4_header_t *frame_ip(const uint8_t *frame)
+{
+    return (orion_ipv4_header_t *)(frame + ETH_HEADER_LEN);
+}
+
+static orion_tcp_header_t *frame_tcp(const uint8_t *frame, const tcp_frame_t *f)
+{
+    return (orion_tcp_header_t *)(frame + ETH_HEADER_LEN + f->ip_len);
+}
+
+// Sum of the pseudo header over a TCP segment of tcp_total bytes
+static uint32_t tcp_pseudo_sum(const orion_ipv4_header_t *ip, size_t tcp_total)
+{
+    uint32_t sum = csum_add(0, (const uint8_t *)&ip->src_addr, 4);
+    sum = csum_add(sum, (const uint8_t *)&ip->dst_addr, 4);
+    return sum + IP_PROTO_TCP + (uint32_t)tcp_total;
+}
+
+static void ip_set_checksum(orion_ipv4_header_t *ip, size_t ip_len)
+{
+    ip->checksum = 0;
+    put_be16((uint8_t *)&ip->checksum, csum_fold(csum_add(0, (const uint8_t *)ip, ip_len)));
+}
+
+static bool parse_tcp_frame(const uint8_t *frame, size_t len, tcp_frame_t *out)
+{
+    if (len < ETH_HEADER_LEN + sizeof(orion_ipv4_header_t) + sizeof(orion_tcp_header_t) ||
+        get_be16(frame + 12) != ETH_TYPE_IPV4) {
+        return false;
+    }
+
+    const orion_ipv4_header_t *ip = frame_ip(frame);
+    size_t ip_len = (size_t)(ip->version_ihl & 0x0F) * 4;
+    if ((ip->version_ihl >> 4) != 4 || ip_len < sizeof(orion_ipv4_header_t) || ip->protocol != IP_PROTO_TCP) {
+        return false;
+    }
+    // Ethernet padding past the IP length is not part of the packet
+    size_t total = ntohs(ip->total_length);
+    if (total < ip_len + sizeof(orion_tcp_header_t) || ETH_HEADER_LEN + total > len) {
+        return false;
+    }
+
+    const orion_tcp_header_t *tcp = (const orion_tcp_header_t *)(frame + ETH_HEADER_LEN + ip_len);
+    size_t tcp_len = (size_t)(tcp->data_offset_reserved >> 4) * 4;
+    if (tcp_len < sizeof(orion_tcp_header_t) || ip_len + tcp_len > total) {
+        return false;
+    }
+
+    out->ip_len = ip_len;
+    out->tcp_len = tcp_len;
+    out->payload_off = ETH_HEADER_LEN + ip_len + tcp_len;
+    out->payload_len = total - ip_len - tcp_len;
+    return true;
+}
+
+/* ============================================================================
+ * Generic Receive Offload
+ * ============================================================================ */
+
+static bool gro_checksum_ok(const uint8_t *frame, const tcp_frame_t *f, uint32_t flags)
+{
+    if (flags & ORION_GRO_CHECKSUM_VALID) {
+        return true;
+    }
+    size_t tcp_total = f->tcp_len + f->payload_len;
+    uint32_t sum = tcp_pseudo_sum(frame_ip(frame), tcp_total);
+    return csum_fold(csum_add(sum, (const uint8_t *)frame_tcp(frame, f), tcp_total)) == 0;
+}
+
+// Segments carrying data and nothing a merge would hide
+static bool gro_mergeable(const uint8_t *frame, const tcp_frame_t *f)
+{
+    const orion_ipv4_header_t *ip = frame_ip(frame);
+    uint16_t fragment = ntohs(ip->flags_offset);
+    return f->payload_len > 0 && !(fragment & (IP_MORE_FRAGMENTS | IP_OFFSET_MASK)) &&
+           !(frame_tcp(frame, f)->flags & GRO_FLUSH_FLAGS);
+}
+
+static bool gro_same_flow(const gro_flow_t *flow, const uint8_t *frame, const tcp_frame_t *f)
+{
+    const orion_ipv4_header_t *held_ip = frame_ip(flow->buf);
+    const orion_ipv4_header_t *ip = frame_ip(frame);
+    const orion_tcp_header_t *held_tcp = (const orion_tcp_header_t *)(flow->buf + ETH_HEADER_LEN +
+                                                                      (size_t)(held_ip->version_ihl & 0x0F) * 4);
+    const orion_tcp_header_t *tcp = frame_tcp(frame, f);
+    return held_ip->src_addr == ip->src_addr && held_ip->dst_addr == ip->dst_addr &&
+           held_tcp->src_port == tcp->src_port && held_tcp->dst_port == tcp->dst_port;
+}
+
+// Everything but the payload, sequence number, IP length, identification and checksums matches
+static bool gro_same_headers(const gro_flow_t *flow, const uint8_t *frame, const tcp_frame_t *f)
+{
+    tcp_frame_t held;
+    parse_tcp_frame(flow->buf, flow->len, &held);
+    if (held.ip_len != f->ip_len || held.tcp_len != f->tcp_len) {
+        return false;
+    }
+
+    const orion_ipv4_header_t *held_ip = frame_ip(flow->buf);
+    const orion_ipv4_header_t *ip = frame_ip(frame);
+    if (held_ip->tos != ip->tos || held_ip->ttl != ip->ttl || held_ip->flags_offset != ip->flags_offset ||
+        memcmp(held_ip + 1, ip + 1, f->ip_len - sizeof(orion_ipv4_header_t)) != 0) {
+        return false;
+    }
+
+    const orion_tcp_header_t *held_tcp = frame_tcp(flow->buf, &held);
+    const orion_tcp_header_t *tcp = frame_tcp(frame, f);
+    return held_tcp->ack_num == tcp->ack_num && held_tcp->window_size == tcp->window_size &&
+           (held_tcp->flags & ~ORION_TCP_FLAG_PSH) == (tcp->flags & ~ORION_TCP_FLAG_PSH) &&
+           memcmp(held_tcp + 1, tcp + 1, f->tcp_len - sizeof(orion_tcp_header_t)) == 0;
+}
+
+static void gro_flush_flow(gro_flow_t *flow)
+{
+    if (!flow->active) {
+        return;
+    }
+    flow->active = false;
+
+    if (flow->segs > 1) {
+        orion_ipv4_header_t *ip = frame_ip(flow->buf);
+        ip->total_length = htons((uint16_t)(flow->len - ETH_HEADER_LEN));
+        ip_set_checksum(ip, (size_t)(ip->version_ihl & 0x0F) * 4);
+    }
+    offload.stats.gro_flushed++;
+    orion_net_process_packet(flow->buf, flow->len);
+}
+
+static gro_flow_t *gro_find(const uint8_t *frame, const tcp_frame_t *f)
+{
+    for (uint32_t i = 0; i < ORION_GRO_MAX_FLOWS; i++) {
+        gro_flow_t *flow = &offload.flows[i];
+        if (flow->active && gro_same_flow(flow, frame, f)) {
+            return flow;
+        }
+    }
+    return NULL;
+}
+
+static gro_flow_t *gro_slot(void)
+{
+    for (uint32_t i = 0; i < ORION_GRO_MAX_FLOWS; i++) {
+        if (!offload.flows[i].active) {
+            return &offload.flows[i];
+        }
+    }
+    gro_flow_t *flow = &offload.flows[offload.evict++ % ORION_GRO_MAX_FLOWS];
+    gro_flush_flow(flow);
+    return flow;
+}
+
+static bool gro_append(gro_flow_t *flow, const uint8_t *frame, const tcp_frame_t *f)
+{
+    const orion_tcp_header_t *tcp = frame_tcp(frame, f);
+    if (ntohl(tcp->seq_num) != flow->next_seq || f->payload_len > flow->mss ||
+        flow->len + f->payload_len > GRO_BUFFER_SIZE || flow->segs >= ORION_GRO_MAX_SEGS ||
+        !gro_same_headers(flow, frame, f)) {
+        return false;
+    }
+
+    memcpy(flow->buf + flow->len, frame + f->payload_off, f->payload_len);
+    flow->len += f->payload_len;
+    flow->next_seq += (uint32_t)f->payload_len;
+    flow->segs++;
+    offload.stats.gro_merged++;
+
+    // A pushed or short segment ends what the sender had to say for now
+    if (tcp->flags & ORION_TCP_FLAG_PSH) {
+        tcp_frame_t held;
+        parse_tcp_frame(flow->buf, flow->len, &held);
+        frame_tcp(flow->buf, &held)->flags |= ORION_TCP_FLAG_PSH;
+    }
+    if ((tcp->flags & ORION_TCP_FLAG_PSH) || f->payload_len < flow->mss) {
+        gro_flush_flow(flow);
+    }
+    return true;
+}
+
+static void gro_hold(const uint8_t *frame, const tcp_frame_t *f)
+{
+    gro_flow_t *flow = gro_slot();
+    if (!flow->buf) {
+        flow->buf = kmalloc(GRO_BUFFER_SIZE);
+        if (!flow->buf) {
+            offload.stats.gro_bypassed++;
+            orion_net_process_packet((void *)frame, f->payload_off + f->payload_len);
+            return;
+        }
+    }
+
+    flow->len = f->payload_off + f->payload_len;
+    memcpy(flow->buf, frame, flow->len);
+    flow->next_seq = ntohl(frame_tcp(frame, f)->seq_num) + (uint32_t)f->payload_len;
+    flow->mss = (uint16_t)f->payload_len;
+    flow->segs = 1;
+    flow->active = true;
+}
+
+int orion_gro_receive(void *frame, size_t len, uint32_t flags)
+{
+    if (!frame || len == 0) {
+        return -1;
+    }
+
+    const uint8_t *bytes = (const uint8_t *)frame;
+    tcp_frame_t f;
+    spinlock_acquire(&offload.lock);
+    if (offload.disabled || !parse_tcp_frame(bytes, len, &f)) {
+        offload.stats.gro_bypassed++;
+        spinlock_release(&offload.lock);
+        return orion_net_process_packet(frame, len);
+    }
+    offload.stats.gro_received++;
+
+    bool mergeable = gro_mergeable(bytes, &f);
+    if (mergeable && !gro_checksum_ok(bytes, &f, flags)) {
+        offload.stats.gro_bad_csum++;
+        mergeable = false;
+    }
+
+    gro_flow_t *flow = gro_find(bytes, &f);
+    if (flow && mergeable && gro_append(flow, bytes, &f)) {
+        spinlock_release(&offload.lock);
+        return 0;
+    }
+
+    // Whatever was held for the flow goes first to keep its order
+    if (flow) {
+        gro_flush_flow(flow);
+    }
+    if (!mergeable || (frame_tcp(bytes, &f)->flags & ORION_TCP_FLAG_PSH)) {
+        offload.stats.gro_bypassed++;
+        spinlock_release(&offload.lock);
+        return orion_net_process_packet(frame, len);
+    }
+    gro_hold(bytes, &f);
+    spinlock_release(&offload.lock);
+    return 0;
+}
+
+void orion_gro_flush(void)
+{
+    spinlock_acquire(&offload.lock);
+    for (uint32_t i = 0; i < ORION_GRO_MAX_FLOWS; i++) {
+        gro_flush_flow(&offload.flows[i]);
+    }
+    spinlock_release(&offload.lock);
+}
+
+void orion_gro_set_enabled(bool enabled)
+{
+    orion_gro_flush();
+    spinlock_acquire(&offload.lock);
+    offload.disabled = !enabled;
+    spinlock_release(&offload.lock);
+    klog_info(KLOG_CAT_KERNEL, "Generic receive offload %s", enabled ? "enabled" : "disabled");
+}
+
+/* ============================================================================
+ * Generic Segmentation Offload
+ * ============================================================================ */
+
+int orion_gso_segment(const void *frame, size_t len, uint16_t mss, bool csum_offload,
+                      int (*emit)(void *ctx, void *segment, size_t len), void *ctx)
+{
+    const uint8_t *bytes = (const uint8_t *)frame;
+    tcp_frame_t f;
+    if (!frame || !emit || mss == 0 || !parse_tcp_frame(bytes, len, &f)) {
+        return -1;
+    }
+
+    size_t header_len = f.payload_off;
+    uint8_t *segment = kmalloc(header_len + mss);
+    if (!segment) {
+        return -1;
+    }
+
+    const orion_tcp_header_t *tcp = frame_tcp(bytes, &f);
+    uint32_t seq = ntohl(tcp->seq_num);
+    uint16_t id = ntohs(frame_ip(bytes)->identification);
+    uint8_t tcp_flags = tcp->flags;
+
+    int count = 0;
+    size_t offset = 0;
+    do {
+        size_t chunk = f.payload_len - offset < mss ? f.payload_len - offset : mss;
+        bool last = offset + chunk >= f.payload_len;
+        memcpy(segment, bytes, header_len);
+        memcpy(segment + header_len, bytes + f.payload_off + offset, chunk);
+
+        orion_ipv4_header_t *ip = frame_ip(segment);
+        ip->total_length = htons((uint16_t)(header_len - ETH_HEADER_LEN + chunk));
+        ip->identification = htons((uint16_t)(id + count));
+        ip_set_checksum(ip, f.ip_len);
+
+        // FIN and PSH belong to the last segment, CWR to the first
+        orion_tcp_header_t *seg_tcp = frame_tcp(segment, &f);
+        seg_tcp->seq_num = htonl(seq + (uint32_t)offset);
+        seg_tcp->flags = tcp_flags;
+        if (!last) {
+            seg_tcp->flags &= ~(ORION_TCP_FLAG_FIN | ORION_TCP_FLAG_PSH);
+        }
+        if (count > 0) {
+            seg_tcp->flags &= ~ORION_TCP_FLAG_CWR;
+        }
+
+        // With checksum offload the device completes the sum seeded with the pseudo header
+        size_t tcp_total = f.tcp_len + chunk;
+        uint32_t sum = tcp_pseudo_sum(ip, tcp_total);
+        seg_tcp->checksum = 0;
+        uint16_t check = csum_offload ? (uint16_t)~csum_fold(sum)
+                                      : csum_fold(csum_add(sum, (const uint8_t *)seg_tcp, tcp_total));
+        put_be16((uint8_t *)&seg_tcp->checksum, check);
+
+        if (emit(ctx, segment, header_len + chunk) != 0) {
+            kfree(segment);
+            return -1;
+        }
+        count++;
+        offset += chunk;
+    } while (offset < f.payload_len);
+
+    kfree(segment);
+    return count;
+}
+
+static int gso_emit_driver(void *ctx, void *segment, size_t len)
+{
+    orion_net_driver_t *driver = (orion_net_driver_t *)ctx;
+    return driver->transmit(driver, segment, len);
+}
+
+int orion_net_transmit(orion_net_driver_t *driver, void *frame, size_t len, uint16_t mss)
+{
+    if (!driver || !driver->transmit || !frame) {
+        return -1;
+    }
+
+    tcp_frame_t f;
+    if (mss == 0 || !parse_tcp_frame((const uint8_t *)frame, len, &f) || f.payload_len <= mss) {
+        return driver->transmit(driver, frame, len);
+    }
+
+    if ((driver->offload_caps & ORION_NET_OFFLOAD_TCP_TSO) && driver->transmit_tso) {
+        spinlock_acquire(&offload.lock);
+        offload.stats.tso_packets++;
+        spinlock_release(&offload.lock);
+        return driver->transmit_tso(driver, frame, len, mss);
+    }
+
+    int segments = orion_gso_segment(frame, len, mss, driver->offload_caps & ORION_NET_OFFLOAD_TCP_CSUM,
+                                     gso_emit_driver, driver);
+    if (segments < 0) {
+        return -1;
+    }
+    spinlock_acquire(&offload.lock);
+    offload.stats.gso_packets++;
+    offload.stats.gso_segments += segments;
+    spinlock_release(&offload.lock);
+    return 0;
+}
+
+void orion_offload_get_stats(orion_offload_stats_t *stats)
+{
+    if (!stats) {
+        return;
+    }
+    spinlock_acquire(&offload.lock);
+    *stats = offload.stats;
+    spinlock_release(&offload.lock);
+}
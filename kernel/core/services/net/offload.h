@@ -0,0 +1,110 @@
+/*
+ * Orion Operating System - Generic Receive and Segmentation Offload
+ *
+ * Per-packet costs of the stack are paid once per large packet rather
+ * than once per wire segment. On receive, consecutive TCP segments of a
+ * flow arriving in one batch from a receive pool are coalesced into one
+ * IPv4 packet of up to 64 KiB before the stack sees them (GRO). On
+ * transmit, a TCP packet larger than the MSS goes to the driver whole
+ * when the device segments it in hardware (TSO), and is cut into
+ * MSS-sized frames here otherwise (GSO).
+ *
+ * Segments are merged only when nothing but their payload differs: same
+ * addresses, ports, acknowledgment, window, TCP options and IP header
+ * fields, contiguous sequence numbers, and no flag other than ACK and
+ * PSH. Transport checksums are verified before merging, unless the
+ * device reported them valid, and the stack does not check them again on
+ * a merged packet. A segment that cannot be merged flushes its flow
+ * first, so a flow's data always reaches the stack in arrival order.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_OFFLOAD_H
+#define ORION_NET_OFFLOAD_H
+
+#include <orion/types.h>
+#include "network_architecture.h"
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_GRO_MAX_FLOWS 8     // Flows held at once
+#define ORION_GRO_MAX_SEGS 64     // Segments merged into one packet
+#define ORION_GRO_MAX_SIZE 65535  // Largest merged IPv4 packet
+
+#define ORION_GRO_CHECKSUM_VALID 0x1 // The device verified the transport checksum
+
+    typedef struct
+    {
+        uint64_t gro_received;  // TCP segments offered to GRO
+        uint64_t gro_merged;    // Segments appended to a held packet
+        uint64_t gro_flushed;   // Packets delivered from GRO
+        uint64_t gro_bypassed;  // Frames delivered as they came
+        uint64_t gro_bad_csum;  // Segments delivered unmerged on a checksum mismatch
+        uint64_t gso_packets;   // Packets segmented in software
+        uint64_t gso_segments;  // Frames produced by GSO
+        uint64_t tso_packets;   // Packets handed to the device for segmentation
+    } orion_offload_stats_t;
+
+    /**
+     * @brief Offer a received frame to GRO
+     * @param frame Ethernet frame; it is copied before being held
+     * @param len Frame length
+     * @param flags ORION_GRO_CHECKSUM_VALID if the device checked the transport checksum
+     * @return 0 on success, negative value on error
+     *
+     * Frames that cannot be merged go to orion_net_process_packet()
+     * immediately; the others are held until orion_gro_flush().
+     */
+    int orion_gro_receive(void *frame, size_t len, uint32_t flags);
+
+    /**
+     * @brief Deliver every held packet, at the end of a receive batch
+     */
+    void orion_gro_flush(void);
+
+    /**
+     * @brief Enable or disable GRO; disabling flushes what is held
+     */
+    void orion_gro_set_enabled(bool enabled);
+
+    /**
+     * @brief Cut a TCP/IPv4 frame into frames of at most mss bytes of payload
+     * @param frame Ethernet frame
+     * @param len Frame length
+     * @param mss Payload per segment
+     * @param csum_offload The device fills in TCP checksums; only the pseudo-header sum is written
+     * @param emit Called with each segment, which is only valid during the call
+     * @param ctx Passed to emit
+     * @return Segments emitted, or -1 if the frame is not TCP/IPv4 or emit failed
+     */
+    int orion_gso_segment(const void *frame, size_t len, uint16_t mss, bool csum_offload,
+                          int (*emit)(void *ctx, void *segment, size_t len), void *ctx);
+
+    /**
+     * @brief Transmit a frame, segmenting it first when the device cannot
+     * @param driver Driver of the outgoing interface
+     * @param frame Ethernet frame
+     * @param len Frame length
+     * @param mss Payload per wire segment for TCP frames
+     * @return 0 on success, negative value on error
+     */
+    int orion_net_transmit(orion_net_driver_t *driver, void *frame, size_t len, uint16_t mss);
+
+    /**
+     * @brief Offload counters
+     */
+    void orion_offload_get_stats(orion_offload_stats_t *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_OFFLOAD_H
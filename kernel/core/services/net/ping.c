@@ -0,0 +1,336 @@
+/*
+ * Orion Operating System - ICMP Echo Sockets Implementation
+ *
+ * Each socket owns a ring of received messages allocated when it is
+ * opened; a full ring drops what arrives until the owner reads.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "ping.h"
+#include "netns.h"
+#include "tcp_ip_stack.h"
+#include <orion/mm.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define PING_STATUS_OK 0
+#define PING_STATUS_EPERM -1
+#define PING_STATUS_EBADF -9
+#define PING_STATUS_EAGAIN -11
+#define PING_STATUS_ENOMEM -12
+#define PING_STATUS_EINVAL -22
+#define PING_STATUS_EMFILE -24
+#define PING_STATUS_EMSGSIZE -90
+#define PING_STATUS_EAFNOSUPPORT -97
+#define PING_STATUS_EHOSTUNREACH -113
+
+typedef struct {
+    uint32_t src_ip;
+    uint8_t type;
+    uint8_t code;
+    uint8_t ttl;
+    uint16_t sequence;
+    uint16_t mtu;
+    uint16_t len;
+    uint8_t data[ORION_PING_MAX_PAYLOAD];
+} ping_message_t;
+
+// A slot is free when it has no queue
+static struct {
+    uint64_t owner;
+    uint16_t identifier;
+    uint32_t local_ip;
+    ping_message_t *queue;
+    uint32_t head;
+    uint32_t count;
+} ping_sockets[ORION_PING_MAX_SOCKETS];
+
+static spinlock_t ping_lock = SPINLOCK_INITIALIZER;
+static uint16_t next_identifier = 1;
+
+static uint32_t get_u16(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8);
+}
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static void put_u16(uint8_t *p, uint16_t v)
+{
+    p[0] = (uint8_t)v;
+    p[1] = (uint8_t)(v >> 8);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static size_t ping_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+// Caller holds ping_lock
+static bool identifier_in_use(uint16_t identifier)
+{
+    for (int i = 0; i < ORION_PING_MAX_SOCKETS; i++) {
+        if (ping_sockets[i].queue && ping_sockets[i].identifier == identifier) {
+            return true;
+        }
+    }
+    return false;
+}
+
+int orion_ping_input(uint32_t src_ip, uint8_t type, uint8_t code, uint16_t identifier, uint16_t sequence,
+                     uint8_t ttl, uint16_t mtu, const void *data, size_t len)
+{
+    if (len && !data) {
+        return -1;
+    }
+    if (len > ORION_PING_MAX_PAYLOAD) {
+        len = ORION_PING_MAX_PAYLOAD;
+    }
+
+    spinlock_acquire(&ping_lock);
+    for (int i = 0; i < ORION_PING_MAX_SOCKETS; i++) {
+        if (!ping_sockets[i].queue || ping_sockets[i].identifier != identifier) {
+            continue;
+        }
+        if (ping_sockets[i].count == ORION_PING_QUEUE_DEPTH) {
+            break;
+        }
+        uint32_t slot = (ping_sockets[i].head + ping_sockets[i].count) % ORION_PING_QUEUE_DEPTH;
+        ping_message_t *message = &ping_sockets[i].queue[slot];
+        message->src_ip = src_ip;
+        message->type = type;
+        message->code = code;
+        message->ttl = ttl;
+        message->sequence = sequence;
+        message->mtu = mtu;
+        message->len = (uint16_t)len;
+        if (len) {
+            memcpy(message->data, data, len);
+        }
+        ping_sockets[i].count++;
+        spinlock_release(&ping_lock);
+        return 0;
+    }
+    spinlock_release(&ping_lock);
+    return -1;
+}
+
+static size_t ping_open(uint64_t sender, const uint8_t *args, size_t args_len, uint8_t *reply)
+{
+    if (args_len < 1) {
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+    if (args[0] == ORION_PING_FAMILY_INET6) {
+        return ping_reply(reply, PING_STATUS_EAFNOSUPPORT, 0);
+    }
+    if (args[0] != ORION_PING_FAMILY_INET) {
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+
+    // Echo requests of a namespace leave from its address
+    uint32_t local_ip = 0;
+    uint32_t ns = orion_netns_of(sender);
+    if (ns != 0 && orion_netns_address(ns, &local_ip) != 0) {
+        return ping_reply(reply, PING_STATUS_ENOMEM, 0);
+    }
+
+    ping_message_t *queue = kmalloc(ORION_PING_QUEUE_DEPTH * sizeof(ping_message_t));
+    if (!queue) {
+        return ping_reply(reply, PING_STATUS_ENOMEM, 0);
+    }
+
+    spinlock_acquire(&ping_lock);
+    int slot = -1;
+    for (int i = 0; i < ORION_PING_MAX_SOCKETS; i++) {
+        if (!ping_sockets[i].queue) {
+            slot = i;
+            break;
+        }
+    }
+    if (slot < 0) {
+        spinlock_release(&ping_lock);
+        kfree(queue);
+        return ping_reply(reply, PING_STATUS_EMFILE, 0);
+    }
+    while (next_identifier == 0 || identifier_in_use(next_identifier)) {
+        next_identifier++;
+    }
+    uint16_t identifier = next_identifier++;
+    ping_sockets[slot].owner = sender;
+    ping_sockets[slot].identifier = identifier;
+    ping_sockets[slot].local_ip = local_ip;
+    ping_sockets[slot].queue = queue;
+    ping_sockets[slot].head = 0;
+    ping_sockets[slot].count = 0;
+    spinlock_release(&ping_lock);
+
+    // Socket identifiers are slot index + 1 so that 0 is never valid
+    put_u32(reply + 4, (uint32_t)slot + 1);
+    put_u16(reply + 8, identifier);
+    return ping_reply(reply, PING_STATUS_OK, 6);
+}
+
+// Caller holds ping_lock
+static int ping_slot(uint32_t id, uint64_t sender)
+{
+    if (id == 0 || id > ORION_PING_MAX_SOCKETS) {
+        return -1;
+    }
+    if (!ping_sockets[id - 1].queue || ping_sockets[id - 1].owner != sender) {
+        return -1;
+    }
+    return (int)id - 1;
+}
+
+static size_t ping_send(int slot, const uint8_t *args, size_t args_len, uint8_t *reply)
+{
+    if (args_len < 12) {
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+    size_t len = args_len - 12;
+    if (len > ORION_PING_MAX_PAYLOAD) {
+        return ping_reply(reply, PING_STATUS_EMSGSIZE, 0);
+    }
+
+    spinlock_acquire(&ping_lock);
+    uint32_t local_ip = ping_sockets[slot].local_ip;
+    uint16_t identifier = ping_sockets[slot].identifier;
+    spinlock_release(&ping_lock);
+
+    uint8_t ttl = args[10] ? args[10] : ORION_IP_DEFAULT_TTL;
+    bool dont_fragment = (args[11] & ORION_PING_DONT_FRAGMENT) != 0;
+    int result = orion_icmp_send_echo(local_ip, get_u32(args + 4), ORION_ICMP_ECHO_REQUEST, identifier,
+                                      (uint16_t)get_u16(args + 8), ttl, dont_fragment, args + 12, len);
+    if (result == ORION_IP_ERR_MSGSIZE) {
+        return ping_reply(reply, PING_STATUS_EMSGSIZE, 0);
+    }
+    if (result != 0) {
+        return ping_reply(reply, PING_STATUS_EHOSTUNREACH, 0);
+    }
+    return ping_reply(reply, PING_STATUS_OK, 0);
+}
+
+static size_t ping_recv(int slot, const uint8_t *args, size_t args_len, uint8_t *reply, size_t reply_capacity)
+{
+    if (args_len < 8 || reply_capacity < 16) {
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+    size_t max = get_u32(args + 4);
+    if (max > reply_capacity - 16) {
+        max = reply_capacity - 16;
+    }
+
+    spinlock_acquire(&ping_lock);
+    if (ping_sockets[slot].count == 0) {
+        spinlock_release(&ping_lock);
+        return ping_reply(reply, PING_STATUS_EAGAIN, 0);
+    }
+    const ping_message_t *message = &ping_sockets[slot].queue[ping_sockets[slot].head];
+    size_t len = message->len < max ? message->len : max;
+    put_u32(reply + 4, message->src_ip);
+    reply[8] = message->type;
+    reply[9] = message->code;
+    put_u16(reply + 10, message->sequence);
+    reply[12] = message->ttl;
+    reply[13] = 0;
+    put_u16(reply + 14, message->mtu);
+    memcpy(reply + 16, message->data, len);
+    ping_sockets[slot].head = (ping_sockets[slot].head + 1) % ORION_PING_QUEUE_DEPTH;
+    ping_sockets[slot].count--;
+    spinlock_release(&ping_lock);
+
+    return ping_reply(reply, PING_STATUS_OK, 12 + len);
+}
+
+static size_t ping_close(int slot, uint8_t *reply)
+{
+    spinlock_acquire(&ping_lock);
+    ping_message_t *queue = ping_sockets[slot].queue;
+    ping_sockets[slot].queue = NULL;
+    ping_sockets[slot].owner = 0;
+    spinlock_release(&ping_lock);
+
+    kfree(queue);
+    return ping_reply(reply, PING_STATUS_OK, 0);
+}
+
+size_t orion_ping_ipc_handle(uint64_t sender, bool privileged, const uint8_t *request, size_t request_len,
+                             uint8_t *reply, size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 12) {
+        return 0;
+    }
+    if (request_len < 4) {
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+
+    uint32_t op = get_u32(request);
+    const uint8_t *args = request + 4;
+    size_t args_len = request_len - 4;
+
+    if (op == ORION_PING_OP_OPEN) {
+        if (!privileged) {
+            return ping_reply(reply, PING_STATUS_EPERM, 0);
+        }
+        return ping_open(sender, args, args_len, reply);
+    }
+
+    if (args_len < 4) {
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+    spinlock_acquire(&ping_lock);
+    int slot = ping_slot(get_u32(args), sender);
+    spinlock_release(&ping_lock);
+    if (slot < 0) {
+        return ping_reply(reply, PING_STATUS_EBADF, 0);
+    }
+
+    switch (op) {
+    case ORION_PING_OP_SEND:
+        return ping_send(slot, args, args_len, reply);
+
+    case ORION_PING_OP_RECV:
+        return ping_recv(slot, args, args_len, reply, reply_capacity);
+
+    case ORION_PING_OP_CLOSE:
+        return ping_close(slot, reply);
+
+    default:
+        return ping_reply(reply, PING_STATUS_EINVAL, 0);
+    }
+}
+
+void orion_ping_release(uint64_t owner)
+{
+    for (int i = 0; i < ORION_PING_MAX_SOCKETS; i++) {
+        spinlock_acquire(&ping_lock);
+        ping_message_t *queue = NULL;
+        if (ping_sockets[i].queue && ping_sockets[i].owner == owner) {
+            queue = ping_sockets[i].queue;
+            ping_sockets[i].queue = NULL;
+            ping_sockets[i].owner = 0;
+        }
+        spinlock_release(&ping_lock);
+
+        if (queue) {
+            kfree(queue);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * Orion Operating System - ICMP Echo Sockets
+ *
+ * Lets diagnostic tools such as orion-ping send ICMP echo requests and
+ * read what comes back without a raw socket. The server gives each echo
+ * socket its own identifier and fills it into the requests; replies and
+ * the ICMP errors quoting one of its requests (destination unreachable,
+ * time exceeded) are queued on the socket carrying that identifier.
+ * Same framing as socket_ipc.h; opcodes start at 32:
+ *
+ *   OPEN   family:u8                                -> socket:u32 identifier:u16
+ *   SEND   socket:u32 dst:u32 sequence:u16 ttl:u8
+ *          flags:u8 data...                         -> (empty)
+ *   RECV   socket:u32 max:u32                       -> src:u32 type:u8 code:u8
+ *                                                      sequence:u16 ttl:u8 pad:u8
+ *                                                      mtu:u16
+ *                                                      data, or -EAGAIN
+ *   CLOSE  socket:u32                               -> (empty)
+ *
+ * SEND with ttl 0 uses the default of 64; flag ORION_PING_DONT_FRAGMENT
+ * sets the don't-fragment bit for path MTU discovery, and SEND answers
+ * -EMSGSIZE when such a request is larger than the MTU of the route
+ * it would leave by. RECV returns one message per
+ * call, truncated to `max`: the echo reply (type 0) with its data, or an
+ * ICMP error with the data of the request it quotes, from the router
+ * that reported it; `mtu` is the next-hop MTU of a fragmentation
+ * needed error and 0 otherwise. Only IPv4 is served for now; OPEN answers
+ * -EAFNOSUPPORT for ORION_PING_FAMILY_INET6.
+ *
+ * Opening an echo socket takes the raw network capability, checked by
+ * the message loop and passed as `privileged`. A sender in a network
+ * namespace sends from its own address.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_PING_H
+#define ORION_NET_PING_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_PING_OP_OPEN 32
+#define ORION_PING_OP_SEND 33
+#define ORION_PING_OP_RECV 34
+#define ORION_PING_OP_CLOSE 35
+
+#define ORION_PING_DONT_FRAGMENT (1U << 0)
+
+#define ORION_PING_FAMILY_INET 2
+#define ORION_PING_FAMILY_INET6 10
+
+#define ORION_PING_MAX_SOCKETS 32
+#define ORION_PING_QUEUE_DEPTH 16   // Messages queued per socket
+#define ORION_PING_MAX_PAYLOAD 1472 // Echo data in one Ethernet frame
+
+    /**
+     * @brief Queue an echo reply or ICMP error on the socket it belongs to
+     * @param src_ip Sender of the message
+     * @param type ICMP type
+     * @param code ICMP code
+     * @param identifier Identifier of the echo request
+     * @param sequence Sequence number of the echo request
+     * @param ttl TTL the message arrived with
+     * @param mtu Next-hop MTU of a fragmentation needed error, 0 otherwise
+     * @param data Echo data
+     * @param len Data length
+     * @return 0 if queued, -1 if no socket has that identifier or its queue is full
+     */
+    int orion_ping_input(uint32_t src_ip, uint8_t type, uint8_t code, uint16_t identifier, uint16_t sequence,
+                         uint8_t ttl, uint16_t mtu, const void *data, size_t len);
+
+    /**
+     * @brief Handle one echo socket request
+     * @param sender PID of the sender
+     * @param privileged Whether the sender holds the raw network capability
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_ping_ipc_handle(uint64_t sender, bool privileged, const uint8_t *request, size_t request_len,
+                                 uint8_t *reply, size_t reply_capacity);
+
+    /**
+     * @brief Close every echo socket owned by an exiting process
+     * @param owner Process identifier
+     */
+    void orion_ping_release(uint64_t owner);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_PING_H
@@ -0,0 +1,700 @@
+/*
+ * Orion Operating System - Routing Tables and Policy Rules Implementation
+ *
+ * Routes of every table share one array and rules are kept sorted by
+ * priority; both are small enough for lookups to scan them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "route.h"
+#include "netns.h"
+#include "tcp_ip_stack.h"
+#include <orion/klog.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define ROUTE_STATUS_OK 0
+#define ROUTE_STATUS_EPERM -1
+#define ROUTE_STATUS_ENOENT -2
+#define ROUTE_STATUS_EEXIST -17
+#define ROUTE_STATUS_EINVAL -22
+#define ROUTE_STATUS_ENOSPC -28
+
+// A slot is free when its table is 0
+static orion_route_t routes[ORION_ROUTE_MAX_ROUTES];
+static orion_route_rule_t rules[ORION_ROUTE_MAX_RULES];
+static int rule_count = 0;
+static spinlock_t route_lock = SPINLOCK_INITIALIZER;
+
+/* ============================================================================
+ * Prefixes and Flow Hashing
+ * ============================================================================ */
+
+static uint8_t family_bits(uint8_t family)
+{
+    switch (family) {
+    case ORION_ROUTE_FAMILY_INET:
+        return 32;
+    case ORION_ROUTE_FAMILY_INET6:
+        return 128;
+    default:
+        return 0;
+    }
+}
+
+static bool prefix_match(const uint8_t *prefix, const uint8_t *addr, uint8_t len)
+{
+    size_t bytes = len / 8;
+    if (memcmp(prefix, addr, bytes) != 0) {
+        return false;
+    }
+    uint8_t bits = len % 8;
+    if (bits == 0) {
+        return true;
+    }
+    uint8_t mask = (uint8_t)(0xFF << (8 - bits));
+    return (prefix[bytes] & mask) == (addr[bytes] & mask);
+}
+
+// Host bits are cleared so that 10.0.0.1/8 and 10.0.0.0/8 are the same route
+static void prefix_normalize(uint8_t *addr, uint8_t len)
+{
+    for (uint8_t bit = len; bit < 128; bit++) {
+        addr[bit / 8] &= (uint8_t)~(0x80 >> (bit % 8));
+    }
+}
+
+static bool address_zero(const uint8_t *addr)
+{
+    for (int i = 0; i < 16; i++) {
+        if (addr[i]) {
+            return false;
+        }
+    }
+    return true;
+}
+
+static uint32_t fnv1a(uint32_t hash, const uint8_t *data, size_t len)
+{
+    for (size_t i = 0; i < len; i++) {
+        hash ^= data[i];
+        hash *= 16777619U;
+    }
+    return hash;
+}
+
+// Packets of one flow always take the same next hop, which keeps TCP in order
+static uint32_t flow_hash(const orion_route_flow_t *flow)
+{
+    size_t addr_len = family_bits(flow->family) / 8;
+    uint8_t tail[5] = {
+        flow->protocol,
+        (uint8_t)(flow->src_port >> 8), (uint8_t)flow->src_port,
+        (uint8_t)(flow->dst_port >> 8), (uint8_t)flow->dst_port,
+    };
+    uint32_t hash = fnv1a(2166136261U, flow->src, addr_len);
+    hash = fnv1a(hash, flow->dst, addr_len);
+    return fnv1a(hash, tail, sizeof(tail));
+}
+
+/* ============================================================================
+ * Tables
+ * ============================================================================ */
+
+static bool route_valid(const orion_route_t *route)
+{
+    uint8_t bits = family_bits(route->family);
+    if (!bits || route->prefix_len > bits || route->table == 0) {
+        return false;
+    }
+    if (route->type < ORION_ROUTE_TYPE_UNICAST || route->type > ORION_ROUTE_TYPE_PROHIBIT) {
+        return false;
+    }
+    if (route->type == ORION_ROUTE_TYPE_UNICAST &&
+        (route->nexthop_count == 0 || route->nexthop_count > ORION_ROUTE_MAX_NEXTHOPS)) {
+        return false;
+    }
+    for (uint32_t i = 0; i < route->nexthop_count && i < ORION_ROUTE_MAX_NEXTHOPS; i++) {
+        // Bounded so the sum of the weights of a route cannot wrap
+        if (route->nexthops[i].interface[0] == '\0' || route->nexthops[i].weight > ORION_ROUTE_MAX_WEIGHT) {
+            return false;
+        }
+    }
+    return true;
+}
+
+static bool route_same(const orion_route_t *route, uint32_t table, uint8_t family, const uint8_t *dst,
+                       uint8_t prefix_len, uint32_t metric)
+{
+    return route->table == table && route->family == family && route->prefix_len == prefix_len &&
+           (metric == ORION_ROUTE_METRIC_ANY || route->metric == metric) &&
+           memcmp(route->dst, dst, sizeof(route->dst)) == 0;
+}
+
+int orion_route_init(void)
+{
+    spinlock_acquire(&route_lock);
+    memset(routes, 0, sizeof(routes));
+    memset(rules, 0, sizeof(rules));
+
+    static const uint32_t default_rules[3][2] = {
+        {0, ORION_ROUTE_TABLE_LOCAL},
+        {32766, ORION_ROUTE_TABLE_MAIN},
+        {32767, ORION_ROUTE_TABLE_DEFAULT},
+    };
+    for (int i = 0; i < 3; i++) {
+        rules[i].priority = default_rules[i][0];
+        rules[i].table = default_rules[i][1];
+        rules[i].action = ORION_ROUTE_RULE_LOOKUP;
+    }
+    rule_count = 3;
+    spinlock_release(&route_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "Routing tables initialized");
+    return 0;
+}
+
+int orion_route_add(const orion_route_t *route)
+{
+    if (!route || !route_valid(route)) {
+        return ROUTE_STATUS_EINVAL;
+    }
+
+    orion_route_t entry;
+    memcpy(&entry, route, sizeof(entry));
+    prefix_normalize(entry.dst, entry.prefix_len);
+    if (entry.type != ORION_ROUTE_TYPE_UNICAST) {
+        entry.nexthop_count = entry.nexthop_count > ORION_ROUTE_MAX_NEXTHOPS ? ORION_ROUTE_MAX_NEXTHOPS
+                                                                              : entry.nexthop_count;
+    }
+    for (uint32_t i = 0; i < entry.nexthop_count; i++) {
+        entry.nexthops[i].interface[31] = '\0';
+    }
+
+    spinlock_acquire(&route_lock);
+    int free_slot = -1;
+    for (int i = 0; i < ORION_ROUTE_MAX_ROUTES; i++) {
+        if (routes[i].table == 0) {
+            if (free_slot < 0) {
+                free_slot = i;
+            }
+        } else if (route_same(&routes[i], entry.table, entry.family, entry.dst, entry.prefix_len,
+                              entry.metric)) {
+            free_slot = i;
+            break;
+        }
+    }
+    if (free_slot < 0) {
+        spinlock_release(&route_lock);
+        klog_error(KLOG_CAT_KERNEL, "Routing tables full");
+        return ROUTE_STATUS_ENOSPC;
+    }
+    routes[free_slot] = entry;
+    spinlock_release(&route_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "Route added to table %u: /%u metric %u, %u next hops",
+              entry.table, entry.prefix_len, entry.metric, entry.nexthop_count);
+    return ROUTE_STATUS_OK;
+}
+
+int orion_route_remove(uint32_t table, uint8_t family, const uint8_t *dst, uint8_t prefix_len,
+                       uint32_t metric)
+{
+    if (!dst || prefix_len > family_bits(family)) {
+        return ROUTE_STATUS_EINVAL;
+    }
+    uint8_t prefix[16];
+    memcpy(prefix, dst, sizeof(prefix));
+    prefix_normalize(prefix, prefix_len);
+
+    spinlock_acquire(&route_lock);
+    for (int i = 0; i < ORION_ROUTE_MAX_ROUTES; i++) {
+        if (routes[i].table != 0 && route_same(&routes[i], table, family, prefix, prefix_len, metric)) {
+            memset(&routes[i], 0, sizeof(routes[i]));
+            spinlock_release(&route_lock);
+            klog_info(KLOG_CAT_KERNEL, "Route removed from table %u: /%u", table, prefix_len);
+            return ROUTE_STATUS_OK;
+        }
+    }
+    spinlock_release(&route_lock);
+    return ROUTE_STATUS_ENOENT;
+}
+
+int orion_route_flush(const char *interface, uint8_t origin)
+{
+    if (!interface) {
+        return 0;
+    }
+
+    int removed = 0;
+    spinlock_acquire(&route_lock);
+    for (int i = 0; i < ORION_ROUTE_MAX_ROUTES; i++) {
+        if (routes[i].table == 0 || (origin && routes[i].origin != origin)) {
+            continue;
+        }
+        for (uint32_t n = 0; n < routes[i].nexthop_count; n++) {
+            if (strcmp(routes[i].nexthops[n].interface, interface) == 0) {
+                memset(&routes[i], 0, sizeof(routes[i]));
+                removed++;
+                break;
+            }
+        }
+    }
+    spinlock_release(&route_lock);
+
+    if (removed) {
+        klog_info(KLOG_CAT_KERNEL, "%d routes through %s flushed", removed, interface);
+    }
+    return removed;
+}
+
+/* ============================================================================
+ * Policy Rules
+ * ============================================================================ */
+
+int orion_route_rule_add(const orion_route_rule_t *rule)
+{
+    if (!rule || rule->src_len > (rule->family ? family_bits(rule->family) : 0)) {
+        return ROUTE_STATUS_EINVAL;
+    }
+    if (rule->action == ORION_ROUTE_RULE_LOOKUP) {
+        if (rule->table == 0) {
+            return ROUTE_STATUS_EINVAL;
+        }
+    } else if (rule->action < ORION_ROUTE_TYPE_BLACKHOLE || rule->action > ORION_ROUTE_TYPE_PROHIBIT) {
+        return ROUTE_STATUS_EINVAL;
+    }
+
+    orion_route_rule_t entry;
+    memcpy(&entry, rule, sizeof(entry));
+    prefix_normalize(entry.src, entry.src_len);
+    entry.iif[31] = '\0';
+    // A mark without a mask has to match exactly
+    if (entry.fwmark && !entry.fwmask) {
+        entry.fwmask = 0xFFFFFFFFU;
+    }
+
+    spinlock_acquire(&route_lock);
+    if (rule_count >= ORION_ROUTE_MAX_RULES) {
+        spinlock_release(&route_lock);
+        return ROUTE_STATUS_ENOSPC;
+    }
+    int at = 0;
+    while (at < rule_count && rules[at].priority < entry.priority) {
+        at++;
+    }
+    if (at < rule_count && rules[at].priority == entry.priority) {
+        spinlock_release(&route_lock);
+        return ROUTE_STATUS_EEXIST;
+    }
+    for (int i = rule_count; i > at; i--) {
+        rules[i] = rules[i - 1];
+    }
+    rules[at] = entry;
+    rule_count++;
+    spinlock_release(&route_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "Routing rule %u added (table %u, action %u)",
+              entry.priority, entry.table, entry.action);
+    return ROUTE_STATUS_OK;
+}
+
+int orion_route_rule_remove(uint32_t priority)
+{
+    spinlock_acquire(&route_lock);
+    for (int i = 0; i < rule_count; i++) {
+        if (rules[i].priority == priority) {
+            for (int j = i; j < rule_count - 1; j++) {
+                rules[j] = rules[j + 1];
+            }
+            rule_count--;
+            spinlock_release(&route_lock);
+            klog_info(KLOG_CAT_KERNEL, "Routing rule %u removed", priority);
+            return ROUTE_STATUS_OK;
+        }
+    }
+    spinlock_release(&route_lock);
+    return ROUTE_STATUS_ENOENT;
+}
+
+static bool rule_matches(const orion_route_rule_t *rule, const orion_route_flow_t *flow)
+{
+    if (rule->family && rule->family != flow->family) {
+        return false;
+    }
+    if (rule->src_len && !prefix_match(rule->src, flow->src, rule->src_len)) {
+        return false;
+    }
+    if ((flow->mark & rule->fwmask) != (rule->fwmark & rule->fwmask)) {
+        return false;
+    }
+    return rule->iif[0] == '\0' || strcmp(rule->iif, flow->iif) == 0;
+}
+
+/* ============================================================================
+ * Lookup
+ * ============================================================================ */
+
+// Longest prefix first, then lowest metric; caller holds route_lock
+static const orion_route_t *table_lookup(uint32_t table, uint8_t family, const uint8_t *addr)
+{
+    const orion_route_t *best = NULL;
+    for (int i = 0; i < ORION_ROUTE_MAX_ROUTES; i++) {
+        const orion_route_t *route = &routes[i];
+        if (route->table != table || route->family != family || !prefix_match(route->dst, addr, route->prefix_len)) {
+            continue;
+        }
+        if (!best || route->prefix_len > best->prefix_len ||
+            (route->prefix_len == best->prefix_len && route->metric < best->metric)) {
+            best = route;
+        }
+    }
+    return best;
+}
+
+static const orion_route_nexthop_t *select_nexthop(const orion_route_t *route, const orion_route_flow_t *flow)
+{
+    if (route->nexthop_count == 1) {
+        return &route->nexthops[0];
+    }
+    uint32_t total = 0;
+    for (uint32_t i = 0; i < route->nexthop_count; i++) {
+        total += route->nexthops[i].weight ? route->nexthops[i].weight : 1;
+    }
+    uint32_t bucket = flow_hash(flow) % total;
+    for (uint32_t i = 0; i < route->nexthop_count; i++) {
+        uint32_t weight = route->nexthops[i].weight ? route->nexthops[i].weight : 1;
+        if (bucket < weight) {
+            return &route->nexthops[i];
+        }
+        bucket -= weight;
+    }
+    return &route->nexthops[route->nexthop_count - 1];
+}
+
+// A sender on the subnet of the ingress interface could reach the gateway itself
+static bool redirect_due(uint32_t table, const orion_route_flow_t *flow, const orion_route_nexthop_t *nexthop)
+{
+    if (flow->family != ORION_ROUTE_FAMILY_INET || flow->iif[0] == '\0' || address_zero(nexthop->gateway) ||
+        strcmp(flow->iif, nexthop->interface) != 0) {
+        return false;
+    }
+    const orion_route_t *source = table_lookup(table, flow->family, flow->src);
+    return source && source->type == ORION_ROUTE_TYPE_UNICAST && address_zero(source->nexthops[0].gateway) &&
+           strcmp(source->nexthops[0].interface, flow->iif) == 0;
+}
+
+int orion_route_lookup(const orion_route_flow_t *flow, orion_route_result_t *result)
+{
+    if (!flow || !result) {
+        return -1;
+    }
+    memset(result, 0, sizeof(*result));
+    result->type = ORION_ROUTE_TYPE_UNREACHABLE;
+    if (!family_bits(flow->family)) {
+        return -1;
+    }
+
+    spinlock_acquire(&route_lock);
+    for (int i = 0; i < rule_count; i++) {
+        const orion_route_rule_t *rule = &rules[i];
+        if (!rule_matches(rule, flow)) {
+            continue;
+        }
+        if (rule->action != ORION_ROUTE_RULE_LOOKUP) {
+            result->type = rule->action;
+            break;
+        }
+
+        const orion_route_t *route = table_lookup(rule->table, flow->family, flow->dst);
+        if (!route) {
+            continue;
+        }
+        result->type = route->type;
+        result->table = route->table;
+        result->metric = route->metric;
+        result->prefix_len = route->prefix_len;
+        if (route->nexthop_count) {
+            const orion_route_nexthop_t *nexthop = select_nexthop(route, flow);
+            memcpy(result->gateway, nexthop->gateway, sizeof(result->gateway));
+            memcpy(result->interface, nexthop->interface, sizeof(result->interface));
+            result->redirect = route->type == ORION_ROUTE_TYPE_UNICAST && redirect_due(rule->table, flow, nexthop);
+        }
+        break;
+    }
+    spinlock_release(&route_lock);
+
+    return result->type == ORION_ROUTE_TYPE_UNICAST || result->type == ORION_ROUTE_TYPE_LOCAL ? 0 : -1;
+}
+
+int orion_route_report(const orion_route_result_t *result, uint32_t local_ip, const void *packet, size_t len)
+{
+    if (!result || !packet || len < sizeof(orion_ipv4_header_t)) {
+        return -1;
+    }
+    const orion_ipv4_header_t *header = packet;
+    uint32_t sender = ntohl(header->src_addr);
+
+    switch (result->type) {
+    case ORION_ROUTE_TYPE_UNREACHABLE:
+        return orion_icmp_send_error(local_ip, sender, 3, 0, 0, packet, len);
+    case ORION_ROUTE_TYPE_PROHIBIT:
+        return orion_icmp_send_error(local_ip, sender, 3, 13, 0, packet, len);
+    case ORION_ROUTE_TYPE_UNICAST:
+        if (result->redirect) {
+            uint32_t gateway = (uint32_t)result->gateway[0] << 24 | (uint32_t)result->gateway[1] << 16 |
+                               (uint32_t)result->gateway[2] << 8 | result->gateway[3];
+            return orion_icmp_send_error(local_ip, sender, 5, 1, gateway, packet, len);
+        }
+        return 0;
+    default:
+        return 0;
+    }
+}
+
+/* ============================================================================
+ * IPC
+ * ============================================================================ */
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static uint16_t get_u16(const uint8_t *p)
+{
+    return (uint16_t)(p[0] | (p[1] << 8));
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static size_t route_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+static void route_decode(const uint8_t *in, orion_route_t *route)
+{
+    memset(route, 0, sizeof(*route));
+    route->table = get_u32(in);
+    route->family = in[4];
+    route->prefix_len = in[5];
+    route->type = in[6];
+    route->origin = in[7];
+    route->metric = get_u32(in + 8);
+    memcpy(route->dst, in + 12, 16);
+    route->nexthop_count = get_u32(in + 28);
+    for (int i = 0; i < ORION_ROUTE_MAX_NEXTHOPS; i++) {
+        const uint8_t *slot = in + 32 + 52 * i;
+        memcpy(route->nexthops[i].gateway, slot, 16);
+        memcpy(route->nexthops[i].interface, slot + 16, 32);
+        route->nexthops[i].interface[31] = '\0';
+        route->nexthops[i].weight = get_u32(slot + 48);
+    }
+}
+
+static void route_encode(const orion_route_t *route, uint8_t *out)
+{
+    memset(out, 0, ORION_ROUTE_RECORD_SIZE);
+    put_u32(out, route->table);
+    out[4] = route->family;
+    out[5] = route->prefix_len;
+    out[6] = route->type;
+    out[7] = route->origin;
+    put_u32(out + 8, route->metric);
+    memcpy(out + 12, route->dst, 16);
+    put_u32(out + 28, route->nexthop_count);
+    for (uint32_t i = 0; i < route->nexthop_count && i < ORION_ROUTE_MAX_NEXTHOPS; i++) {
+        uint8_t *slot = out + 32 + 52 * i;
+        memcpy(slot, route->nexthops[i].gateway, 16);
+        memcpy(slot + 16, route->nexthops[i].interface, 32);
+        put_u32(slot + 48, route->nexthops[i].weight);
+    }
+}
+
+static void rule_decode(const uint8_t *in, orion_route_rule_t *rule)
+{
+    memset(rule, 0, sizeof(*rule));
+    rule->priority = get_u32(in);
+    rule->table = get_u32(in + 4);
+    rule->action = in[8];
+    rule->family = in[9];
+    rule->src_len = in[10];
+    rule->fwmark = get_u32(in + 12);
+    rule->fwmask = get_u32(in + 16);
+    memcpy(rule->src, in + 20, 16);
+    memcpy(rule->iif, in + 36, 32);
+    rule->iif[31] = '\0';
+}
+
+static void rule_encode(const orion_route_rule_t *rule, uint8_t *out)
+{
+    memset(out, 0, ORION_ROUTE_RULE_RECORD_SIZE);
+    put_u32(out, rule->priority);
+    put_u32(out + 4, rule->table);
+    out[8] = rule->action;
+    out[9] = rule->family;
+    out[10] = rule->src_len;
+    put_u32(out + 12, rule->fwmark);
+    put_u32(out + 16, rule->fwmask);
+    memcpy(out + 20, rule->src, 16);
+    memcpy(out + 36, rule->iif, 32);
+}
+
+// Records are copied out under the lock one at a time, the table may change in between
+static size_t route_list(uint32_t table, uint8_t *reply, size_t reply_capacity)
+{
+    size_t len = 0;
+    for (int i = 0; i < ORION_ROUTE_MAX_ROUTES && 4 + len + ORION_ROUTE_RECORD_SIZE <= reply_capacity; i++) {
+        orion_route_t route;
+        spinlock_acquire(&route_lock);
+        route = routes[i];
+        spinlock_release(&route_lock);
+        if (route.table == 0 || (table && route.table != table)) {
+            continue;
+        }
+        route_encode(&route, reply + 4 + len);
+        len += ORION_ROUTE_RECORD_SIZE;
+    }
+    return route_reply(reply, ROUTE_STATUS_OK, len);
+}
+
+static size_t rule_list(uint8_t *reply, size_t reply_capacity)
+{
+    size_t len = 0;
+    spinlock_acquire(&route_lock);
+    for (int i = 0; i < rule_count && 4 + len + ORION_ROUTE_RULE_RECORD_SIZE <= reply_capacity; i++) {
+        rule_encode(&rules[i], reply + 4 + len);
+        len += ORION_ROUTE_RULE_RECORD_SIZE;
+    }
+    spinlock_release(&route_lock);
+    return route_reply(reply, ROUTE_STATUS_OK, len);
+}
+
+static size_t route_get(const uint8_t *args, size_t args_len, uint8_t *reply, size_t reply_capacity)
+{
+    if (args_len < ORION_ROUTE_FLOW_RECORD_SIZE) {
+        return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+    }
+    if (reply_capacity < 4 + ORION_ROUTE_RESULT_RECORD_SIZE) {
+        return route_reply(reply, ROUTE_STATUS_ENOSPC, 0);
+    }
+
+    orion_route_flow_t flow;
+    memset(&flow, 0, sizeof(flow));
+    flow.family = args[0];
+    flow.protocol = args[1];
+    flow.mark = get_u32(args + 4);
+    flow.src_port = get_u16(args + 8);
+    flow.dst_port = get_u16(args + 10);
+    memcpy(flow.src, args + 12, 16);
+    memcpy(flow.dst, args + 28, 16);
+    memcpy(flow.iif, args + 44, 32);
+    flow.iif[31] = '\0';
+
+    orion_route_result_t result;
+    orion_route_lookup(&flow, &result);
+
+    uint8_t *out = reply + 4;
+    memset(out, 0, ORION_ROUTE_RESULT_RECORD_SIZE);
+    put_u32(out, result.type);
+    put_u32(out + 4, result.table);
+    put_u32(out + 8, result.metric);
+    out[12] = result.prefix_len;
+    out[13] = result.redirect ? 1 : 0;
+    memcpy(out + 16, result.gateway, 16);
+    memcpy(out + 32, result.interface, 32);
+    return route_reply(reply, ROUTE_STATUS_OK, ORION_ROUTE_RESULT_RECORD_SIZE);
+}
+
+size_t orion_route_ipc_handle(uint64_t sender, bool admin, const uint8_t *request, size_t request_len,
+                              uint8_t *reply, size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 4) {
+        return 0;
+    }
+    if (request_len < 4) {
+        return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+    }
+    // Namespaces only have their bridge route, which is not theirs to see or change
+    if (orion_netns_of(sender) != 0) {
+        return route_reply(reply, ROUTE_STATUS_EPERM, 0);
+    }
+
+    uint32_t op = get_u32(request);
+    const uint8_t *args = request + 4;
+    size_t args_len = request_len - 4;
+    bool mutates = op == ORION_ROUTE_OP_ADD || op == ORION_ROUTE_OP_DEL || op == ORION_ROUTE_OP_RULE_ADD ||
+                   op == ORION_ROUTE_OP_RULE_DEL || op == ORION_ROUTE_OP_FLUSH;
+    if (mutates && !admin) {
+        return route_reply(reply, ROUTE_STATUS_EPERM, 0);
+    }
+
+    switch (op) {
+    case ORION_ROUTE_OP_ADD: {
+        if (args_len < ORION_ROUTE_RECORD_SIZE) {
+            return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+        }
+        orion_route_t route;
+        route_decode(args, &route);
+        return route_reply(reply, orion_route_add(&route), 0);
+    }
+
+    case ORION_ROUTE_OP_DEL:
+        if (args_len < 28) {
+            return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+        }
+        return route_reply(reply, orion_route_remove(get_u32(args), args[4], args + 12, args[5], get_u32(args + 8)),
+                           0);
+
+    case ORION_ROUTE_OP_LIST:
+        return route_list(args_len >= 4 ? get_u32(args) : 0, reply, reply_capacity);
+
+    case ORION_ROUTE_OP_GET:
+        return route_get(args, args_len, reply, reply_capacity);
+
+    case ORION_ROUTE_OP_RULE_ADD: {
+        if (args_len < ORION_ROUTE_RULE_RECORD_SIZE) {
+            return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+        }
+        orion_route_rule_t rule;
+        rule_decode(args, &rule);
+        return route_reply(reply, orion_route_rule_add(&rule), 0);
+    }
+
+    case ORION_ROUTE_OP_RULE_DEL:
+        if (args_len < 4) {
+            return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+        }
+        return route_reply(reply, orion_route_rule_remove(get_u32(args)), 0);
+
+    case ORION_ROUTE_OP_RULE_LIST:
+        return rule_list(reply, reply_capacity);
+
+    case ORION_ROUTE_OP_FLUSH: {
+        if (args_len < 33 || reply_capacity < 8) {
+            return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+        }
+        char interface[32];
+        memcpy(interface, args, sizeof(interface));
+        interface[31] = '\0';
+        put_u32(reply + 4, (uint32_t)orion_route_flush(interface, args[32]));
+        return route_reply(reply, ROUTE_STATUS_OK, 4);
+    }
+
+    default:
+        return route_reply(reply, ROUTE_STATUS_EINVAL, 0);
+    }
+}
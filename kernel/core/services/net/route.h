@@ -0,0 +1,254 @@
+/*
+ * Orion Operating System - Routing Tables and Policy Rules
+ *
+ * The network server keeps up to ORION_ROUTE_MAX_ROUTES IPv4 and IPv6
+ * routes spread over numbered tables: "local" (255) for the addresses of
+ * the host, "main" (254) for everything configured by orion-net and DHCP,
+ * "default" (253) and any table in between for policy routing. Policy
+ * rules are tried by increasing priority; a rule selects on the source
+ * prefix, the ingress interface and the packet mark under a mask, and
+ * either looks its table up or ends the lookup with an unreachable,
+ * prohibit or blackhole verdict. A table lookup picks the longest
+ * matching prefix, then the lowest metric. A route with several next
+ * hops spreads flows over them by a hash of the addresses, protocol and
+ * ports, in proportion to their weights.
+ *
+ * Management goes through the message loop with the framing of
+ * socket_ipc.h; opcodes start at 24:
+ *
+ *   ROUTE_ADD    route record                          -> (empty)
+ *   ROUTE_DEL    first 28 bytes of a route record      -> (empty)
+ *   ROUTE_LIST   table:u32 (0 for every table)         -> route records
+ *   ROUTE_GET    flow record                           -> result record
+ *   RULE_ADD     rule record                           -> (empty)
+ *   RULE_DEL     priority:u32                          -> (empty)
+ *   RULE_LIST    (none)                                -> rule records
+ *   ROUTE_FLUSH  interface[32] origin:u8               -> removed:u32
+ *
+ * A route record (ORION_ROUTE_RECORD_SIZE bytes) is table:u32 family:u8
+ * prefix_len:u8 type:u8 origin:u8 metric:u32 dst[16] nexthops:u32, then
+ * ORION_ROUTE_MAX_NEXTHOPS slots of gateway[16] interface[32] weight:u32.
+ * A rule record (ORION_ROUTE_RULE_RECORD_SIZE bytes) is priority:u32
+ * table:u32 action:u8 family:u8 src_len:u8 pad:u8 fwmark:u32 fwmask:u32
+ * src[16] iif[32]. A flow record (ORION_ROUTE_FLOW_RECORD_SIZE bytes) is
+ * family:u8 protocol:u8 pad[2] mark:u32 src_port:u16 dst_port:u16
+ * src[16] dst[16] iif[32] and the result record
+ * (ORION_ROUTE_RESULT_RECORD_SIZE bytes) type:u32 table:u32 metric:u32
+ * prefix_len:u8 redirect:u8 pad[2] gateway[16] interface[32]. IPv4
+ * addresses take the first 4 bytes of an address field, in network
+ * order. ROUTE_DEL with metric ORION_ROUTE_METRIC_ANY removes the first
+ * route of that prefix whatever its metric; ROUTE_FLUSH removes the
+ * routes through an interface, restricted to one origin unless origin is
+ * 0, which is how DHCP drops the routes of an expired lease.
+ *
+ * Everything but ROUTE_GET, ROUTE_LIST and RULE_LIST requires an
+ * administrative caller; senders in a network namespace are refused.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_ROUTE_H
+#define ORION_NET_ROUTE_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_ROUTE_OP_ADD 24
+#define ORION_ROUTE_OP_DEL 25
+#define ORION_ROUTE_OP_LIST 26
+#define ORION_ROUTE_OP_GET 27
+#define ORION_ROUTE_OP_RULE_ADD 28
+#define ORION_ROUTE_OP_RULE_DEL 29
+#define ORION_ROUTE_OP_RULE_LIST 30
+#define ORION_ROUTE_OP_FLUSH 31
+
+#define ORION_ROUTE_FAMILY_INET 2
+#define ORION_ROUTE_FAMILY_INET6 10
+
+#define ORION_ROUTE_TABLE_DEFAULT 253
+#define ORION_ROUTE_TABLE_MAIN 254
+#define ORION_ROUTE_TABLE_LOCAL 255
+
+// Route types; BLACKHOLE, UNREACHABLE and PROHIBIT double as rule actions
+#define ORION_ROUTE_TYPE_UNICAST 1
+#define ORION_ROUTE_TYPE_LOCAL 2
+#define ORION_ROUTE_TYPE_BLACKHOLE 3
+#define ORION_ROUTE_TYPE_UNREACHABLE 4
+#define ORION_ROUTE_TYPE_PROHIBIT 5
+
+#define ORION_ROUTE_RULE_LOOKUP 0
+
+// Who installed a route
+#define ORION_ROUTE_ORIGIN_KERNEL 1
+#define ORION_ROUTE_ORIGIN_STATIC 2
+#define ORION_ROUTE_ORIGIN_DHCP 3
+
+#define ORION_ROUTE_METRIC_ANY 0xFFFFFFFFU
+
+#define ORION_ROUTE_MAX_ROUTES 512
+#define ORION_ROUTE_MAX_RULES 64
+#define ORION_ROUTE_MAX_NEXTHOPS 4
+#define ORION_ROUTE_MAX_WEIGHT 256
+
+#define ORION_ROUTE_RECORD_SIZE 240
+#define ORION_ROUTE_RULE_RECORD_SIZE 68
+#define ORION_ROUTE_FLOW_RECORD_SIZE 76
+#define ORION_ROUTE_RESULT_RECORD_SIZE 64
+
+    typedef struct
+    {
+        uint8_t gateway[16]; // All zero for a directly connected destination
+        char interface[32];
+        uint32_t weight; // Share of the flows, 0 counts as 1, at most ORION_ROUTE_MAX_WEIGHT
+    } orion_route_nexthop_t;
+
+    typedef struct
+    {
+        uint32_t table;
+        uint8_t family;
+        uint8_t prefix_len;
+        uint8_t type;
+        uint8_t origin;
+        uint32_t metric;
+        uint8_t dst[16];
+        uint32_t nexthop_count; // At least 1 for unicast routes
+        orion_route_nexthop_t nexthops[ORION_ROUTE_MAX_NEXTHOPS];
+    } orion_route_t;
+
+    typedef struct
+    {
+        uint32_t priority; // Unique, lower is tried first
+        uint32_t table;    // Table looked up by ORION_ROUTE_RULE_LOOKUP
+        uint8_t action;
+        uint8_t family; // 0 matches both families
+        uint8_t src_len;
+        uint32_t fwmark;
+        uint32_t fwmask;
+        uint8_t src[16];
+        char iif[32]; // Empty matches every ingress interface
+    } orion_route_rule_t;
+
+    typedef struct
+    {
+        uint8_t family;
+        uint8_t protocol;
+        uint16_t src_port;
+        uint16_t dst_port;
+        uint32_t mark;
+        uint8_t src[16];
+        uint8_t dst[16];
+        char iif[32]; // Empty for locally generated packets
+    } orion_route_flow_t;
+
+    typedef struct
+    {
+        uint8_t type;
+        bool redirect; // Forwarded back out of its ingress interface to an on-link sender
+        uint8_t prefix_len;
+        uint32_t table;
+        uint32_t metric;
+        uint8_t gateway[16]; // Next hop, all zero when the destination is on-link
+        char interface[32];
+    } orion_route_result_t;
+
+    /**
+     * @brief Reset the tables and install the default rules
+     *
+     * Rules 0, 32766 and 32767 look up the local, main and default tables.
+     *
+     * @return 0 on success
+     */
+    int orion_route_init(void);
+
+    /**
+     * @brief Add a route, replacing one with the same table, prefix and metric
+     * @param route Route to add
+     * @return 0 on success, negative errno on error
+     */
+    int orion_route_add(const orion_route_t *route);
+
+    /**
+     * @brief Remove a route
+     * @param table Table of the route
+     * @param family Address family
+     * @param dst Destination prefix
+     * @param prefix_len Prefix length
+     * @param metric Metric of the route or ORION_ROUTE_METRIC_ANY
+     * @return 0 on success, negative errno on error
+     */
+    int orion_route_remove(uint32_t table, uint8_t family, const uint8_t *dst, uint8_t prefix_len,
+                           uint32_t metric);
+
+    /**
+     * @brief Remove every route going through an interface
+     * @param interface Interface name
+     * @param origin Only remove routes of this origin, 0 for any
+     * @return Number of routes removed
+     */
+    int orion_route_flush(const char *interface, uint8_t origin);
+
+    /**
+     * @brief Add a policy rule
+     * @param rule Rule to add
+     * @return 0 on success, negative errno on error
+     */
+    int orion_route_rule_add(const orion_route_rule_t *rule);
+
+    /**
+     * @brief Remove the policy rule with a priority
+     * @param priority Priority of the rule
+     * @return 0 on success, negative errno on error
+     */
+    int orion_route_rule_remove(uint32_t priority);
+
+    /**
+     * @brief Route a flow through the policy rules and tables
+     * @param flow Flow to route
+     * @param result Filled with the verdict, also when no next hop was chosen
+     * @return 0 for unicast and local destinations, -1 otherwise
+     */
+    int orion_route_lookup(const orion_route_flow_t *flow, orion_route_result_t *result);
+
+    /**
+     * @brief Answer a forwarded IPv4 packet with the ICMP error its route calls for
+     *
+     * Unreachable routes get a destination unreachable (network), prohibit
+     * routes an administratively prohibited and redirects a host redirect
+     * naming the better gateway. Blackholes stay silent. Meant for the
+     * forwarding path, which the stack does not have yet.
+     *
+     * @param result Lookup result of the packet
+     * @param local_ip Address of the server on the ingress interface, host order
+     * @param packet Offending packet, starting with its IP header
+     * @param len Packet length
+     * @return 0 when an error was sent or none was due, -1 on error
+     */
+    int orion_route_report(const orion_route_result_t *result, uint32_t local_ip, const void *packet,
+                           size_t len);
+
+    /**
+     * @brief Handle one routing request
+     * @param sender PID of the sender
+     * @param admin Whether the sender holds administrative rights on the network server
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_route_ipc_handle(uint64_t sender, bool admin, const uint8_t *request, size_t request_len,
+                                  uint8_t *reply, size_t reply_capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_ROUTE_H
@@ -0,0 +1,442 @@
+/*
+ * Orion Operating System - Zero-Copy Receive Pools Implementation
+ *
+ * Frames are handed to GRO (offload.h) inside the pool buffer the device
+ * wrote them to; only TCP segments it holds for merging are copied, and
+ * it delivers them at the end of each batch. The region layout is the
+ * one of lib/orion_rxpool/src/pool.rs. The server keeps its own copy of
+ * the indices it owns and bounds every index, buffer and length read
+ * from the region, which the driver can rewrite at any time. Buffers go back on the fill ring before DELIVER
+ * replies, so a driver naming a buffer twice only sees its own frames
+ * repeated.
+ *
+ * While socket memory is under pressure a pool is paused: frames stay on
+ * the rx ring and their buffers off the fill ring, DELIVER replies with
+ * the backpressure flag, and the pool is drained again once the socket
+ * layer calls orion_rx_pool_resume().
+ *
+ * A driver is followed by device_hold.c from its first pool registered
+ * to its last pool gone, so that its device is held while sockets are
+ * open.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "rx_pool.h"
+#include "device_hold.h"
+#include "network_architecture.h"
+#include "offload.h"
+#include "socket_memory.h"
+#include <orion/klog.h>
+#include <orion/spinlock.h>
+#include <orion/string.h>
+#include <string.h>
+
+#define POOL_STATUS_OK 0
+#define POOL_STATUS_ENOENT -2
+#define POOL_STATUS_EIO -5
+#define POOL_STATUS_EBUSY -16
+#define POOL_STATUS_EINVAL -22
+#define POOL_STATUS_ENOSPC -28
+
+// Region layout
+#define POOL_MAGIC 0x5058524FU // "ORXP"
+#define POOL_VERSION 1
+#define POOL_HEADER_SIZE 0x40
+#define POOL_FILL_ENTRY_SIZE 4
+#define POOL_RX_ENTRY_SIZE 12
+#define POOL_RX_CHECKSUM_VALID 0x01 // The device verified the transport checksum
+#define POOL_BUFFER_ALIGN 64
+#define POOL_PAGE_SIZE 4096 // Buffers of whole pages are page aligned
+
+#define POOL_MAGIC_FIELD 0x00
+#define POOL_VERSION_FIELD 0x04
+#define POOL_BUFFER_COUNT 0x08
+#define POOL_BUFFER_SIZE 0x0C
+#define POOL_FILL_HEAD 0x10
+#define POOL_FILL_TAIL 0x14
+#define POOL_RX_HEAD 0x18
+#define POOL_RX_TAIL 0x1C
+#define POOL_NODE 0x20 // Published by the driver, reported only
+#define POOL_LOW_WATERMARK 0x24
+#define POOL_HIGH_WATERMARK 0x28
+#define POOL_EXHAUSTED 0x2C
+#define POOL_SHORTFALL 0x30
+
+typedef struct {
+    uint8_t *base;
+    size_t size;
+    uint64_t owner;
+    uint32_t buffer_count;
+    uint32_t buffer_size;
+    size_t rx_offset;
+    size_t buffers_offset;
+    uint32_t fill_tail; // Private copies of the indices the server owns
+    uint32_t rx_head;
+    uint64_t refused;   // Entries naming bytes outside the pool
+    bool busy;          // A DELIVER is running the pool
+    bool paused;        // Frames left queued under socket memory pressure
+} rx_pool_t;
+
+static rx_pool_t pools[ORION_RX_POOL_MAX_POOLS];
+static spinlock_t pool_lock = SPINLOCK_INITIALIZER;
+
+static uint32_t get_u16(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8);
+}
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static size_t pool_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+/* ============================================================================
+ * Shared Region Access
+ * ============================================================================ */
+
+static uint32_t *pool_field(const rx_pool_t *pool, size_t offset)
+{
+    return (uint32_t *)(pool->base + offset);
+}
+
+static uint32_t pool_load(const rx_pool_t *pool, size_t offset)
+{
+    return __atomic_load_n(pool_field(pool, offset), __ATOMIC_ACQUIRE);
+}
+
+static void pool_store(const rx_pool_t *pool, size_t offset, uint32_t value)
+{
+    __atomic_store_n(pool_field(pool, offset), value, __ATOMIC_RELEASE);
+}
+
+static size_t pool_buffer_align(uint32_t size)
+{
+    return (size % POOL_PAGE_SIZE) == 0 ? POOL_PAGE_SIZE : POOL_BUFFER_ALIGN;
+}
+
+static bool pool_valid_layout(uint32_t count, uint32_t size)
+{
+    return count != 0 && (count & (count - 1)) == 0 && count <= ORION_RX_POOL_MAX_BUFFERS && size != 0 &&
+           size <= ORION_RX_POOL_MAX_BUFFER_SIZE && (size % POOL_BUFFER_ALIGN) == 0;
+}
+
+// Put a buffer back on the fill ring
+static int pool_recycle(rx_pool_t *pool, uint32_t buffer)
+{
+    uint32_t used = pool->fill_tail - pool_load(pool, POOL_FILL_HEAD);
+    if (used >= pool->buffer_count) {
+        return POOL_STATUS_EIO;
+    }
+    size_t slot = pool->fill_tail & (pool->buffer_count - 1);
+    __atomic_store_n(pool_field(pool, POOL_HEADER_SIZE + slot * POOL_FILL_ENTRY_SIZE), buffer, __ATOMIC_RELAXED);
+    pool->fill_tail++;
+    pool_store(pool, POOL_FILL_TAIL, pool->fill_tail);
+    return POOL_STATUS_OK;
+}
+
+/* ============================================================================
+ * Frame Delivery
+ * ============================================================================ */
+
+// Run every queued frame through GRO and recycle its buffer, stopping
+// early while socket memory is under pressure
+static int pool_drain(rx_pool_t *pool, uint32_t *handled)
+{
+    *handled = 0;
+    for (;;) {
+        uint32_t queued = pool_load(pool, POOL_RX_TAIL) - pool->rx_head;
+        if (queued > pool->buffer_count) {
+            return POOL_STATUS_EIO;
+        }
+        pool->paused = queued != 0 && orion_sockmem_level() == ORION_SOCKMEM_PRESSURE;
+        if (queued == 0 || pool->paused) {
+            return POOL_STATUS_OK;
+        }
+
+        uint8_t entry[POOL_RX_ENTRY_SIZE];
+        size_t slot = pool->rx_head & (pool->buffer_count - 1);
+        memcpy(entry, pool->base + pool->rx_offset + slot * POOL_RX_ENTRY_SIZE, POOL_RX_ENTRY_SIZE);
+        pool->rx_head++;
+        pool_store(pool, POOL_RX_HEAD, pool->rx_head);
+
+        uint32_t buffer = get_u32(entry);
+        uint32_t offset = get_u16(entry + 4);
+        uint32_t length = get_u16(entry + 6);
+        uint32_t flags = entry[9] & POOL_RX_CHECKSUM_VALID ? ORION_GRO_CHECKSUM_VALID : 0;
+        if (buffer >= pool->buffer_count) {
+            pool->refused++;
+            continue;
+        }
+        // A zero-length entry returns a buffer the device failed to fill
+        if (offset + length > pool->buffer_size) {
+            pool->refused++;
+        } else if (length != 0) {
+            uint8_t *frame = pool->base + pool->buffers_offset + (size_t)buffer * pool->buffer_size + offset;
+            orion_gro_receive(frame, length, flags);
+            (*handled)++;
+        }
+
+        int status = pool_recycle(pool, buffer);
+        if (status != POOL_STATUS_OK) {
+            return status;
+        }
+    }
+}
+
+// A batch ends with the pool drained or paused: what GRO holds goes up
+static int pool_process(rx_pool_t *pool, uint32_t *handled)
+{
+    int status = pool_drain(pool, handled);
+    orion_gro_flush();
+    return status;
+}
+
+/* ============================================================================
+ * Registration
+ * ============================================================================ */
+
+int orion_rx_pool_register(uint64_t owner, void *base, size_t size, uint32_t *id)
+{
+    if (!base || !id || ((uintptr_t)base & 7) || size < POOL_HEADER_SIZE) {
+        return POOL_STATUS_EINVAL;
+    }
+
+    rx_pool_t pool = {0};
+    pool.base = base;
+    pool.size = size;
+    pool.owner = owner;
+    if (pool_load(&pool, POOL_MAGIC_FIELD) != POOL_MAGIC || pool_load(&pool, POOL_VERSION_FIELD) != POOL_VERSION) {
+        return POOL_STATUS_EINVAL;
+    }
+    pool.buffer_count = pool_load(&pool, POOL_BUFFER_COUNT);
+    pool.buffer_size = pool_load(&pool, POOL_BUFFER_SIZE);
+    if (!pool_valid_layout(pool.buffer_count, pool.buffer_size)) {
+        return POOL_STATUS_EINVAL;
+    }
+    pool.rx_offset = POOL_HEADER_SIZE + (size_t)pool.buffer_count * POOL_FILL_ENTRY_SIZE;
+    size_t align = pool_buffer_align(pool.buffer_size);
+    pool.buffers_offset =
+        (pool.rx_offset + (size_t)pool.buffer_count * POOL_RX_ENTRY_SIZE + align - 1) & ~(align - 1);
+    if (size < pool.buffers_offset + (size_t)pool.buffer_count * pool.buffer_size) {
+        return POOL_STATUS_EINVAL;
+    }
+    pool.fill_tail = pool_load(&pool, POOL_FILL_TAIL);
+    pool.rx_head = pool_load(&pool, POOL_RX_HEAD);
+
+    int status = POOL_STATUS_ENOSPC;
+    uint32_t owned = 0;
+    spinlock_acquire(&pool_lock);
+    for (uint32_t i = 0; i < ORION_RX_POOL_MAX_POOLS; i++) {
+        if (pools[i].base && pools[i].owner == owner) {
+            owned++;
+        }
+    }
+    for (uint32_t i = 0; i < ORION_RX_POOL_MAX_POOLS && owned < ORION_RX_POOL_MAX_PER_PROCESS; i++) {
+        if (!pools[i].base) {
+            pools[i] = pool;
+            *id = i + 1;
+            status = POOL_STATUS_OK;
+            break;
+        }
+    }
+    spinlock_release(&pool_lock);
+
+    if (status == POOL_STATUS_OK) {
+        uint32_t node = pool_load(&pool, POOL_NODE);
+        if (node == ORION_RX_POOL_NODE_ANY) {
+            klog_info(KLOG_CAT_KERNEL, "Receive pool %u registered: %u buffers of %u bytes", *id, pool.buffer_count,
+                      pool.buffer_size);
+        } else {
+            klog_info(KLOG_CAT_KERNEL, "Receive pool %u registered: %u buffers of %u bytes on node %u", *id,
+                      pool.buffer_count, pool.buffer_size, node);
+        }
+        if (owned == 0) {
+            orion_device_hold_attach(owner);
+        }
+    }
+    return status;
+}
+
+void *orion_rx_pool_unregister(uint64_t owner, uint32_t id)
+{
+    void *base = NULL;
+
+    if (id == 0 || id > ORION_RX_POOL_MAX_POOLS) {
+        return NULL;
+    }
+    spinlock_acquire(&pool_lock);
+    rx_pool_t *pool = &pools[id - 1];
+    if (pool->base && pool->owner == owner && !pool->busy) {
+        base = pool->base;
+        if (pool->refused) {
+            klog_warning(KLOG_CAT_KERNEL, "Receive pool %u refused %llu malformed entries", id,
+                         (unsigned long long)pool->refused);
+        }
+        uint32_t exhausted = pool_load(pool, POOL_EXHAUSTED);
+        if (exhausted) {
+            klog_info(KLOG_CAT_KERNEL, "Receive pool %u ran dry on %u refills, %u descriptors left empty", id,
+                      exhausted, pool_load(pool, POOL_SHORTFALL));
+        }
+        memset(pool, 0, sizeof(*pool));
+    }
+    uint32_t left = 0;
+    for (uint32_t i = 0; i < ORION_RX_POOL_MAX_POOLS; i++) {
+        if (pools[i].base && pools[i].owner == owner) {
+            left++;
+        }
+    }
+    spinlock_release(&pool_lock);
+
+    if (base && left == 0) {
+        orion_device_hold_detach(owner);
+    }
+    return base;
+}
+
+/* ============================================================================
+ * Statistics
+ * ============================================================================ */
+
+// One record per pool, as many as fit in the reply
+static size_t pool_stats(uint8_t *reply, size_t reply_capacity)
+{
+    uint32_t count = 0;
+    size_t length = 4;
+
+    spinlock_acquire(&pool_lock);
+    for (uint32_t i = 0; i < ORION_RX_POOL_MAX_POOLS; i++) {
+        const rx_pool_t *pool = &pools[i];
+        if (!pool->base || 4 + length + ORION_RX_POOL_RECORD_SIZE > reply_capacity) {
+            continue;
+        }
+        uint32_t record[] = {
+            i + 1,
+            pool_load(pool, POOL_NODE),
+            pool->buffer_count,
+            pool->buffer_size,
+            pool->paused ? pool_load(pool, POOL_RX_TAIL) - pool->rx_head : 0,
+            pool_load(pool, POOL_LOW_WATERMARK),
+            pool_load(pool, POOL_HIGH_WATERMARK),
+            pool_load(pool, POOL_EXHAUSTED),
+            pool_load(pool, POOL_SHORTFALL),
+        };
+        for (size_t field = 0; field < sizeof(record) / sizeof(record[0]); field++) {
+            put_u32(reply + 4 + length + field * 4, record[field]);
+        }
+        length += ORION_RX_POOL_RECORD_SIZE;
+        count++;
+    }
+    spinlock_release(&pool_lock);
+
+    put_u32(reply + 4, count);
+    return pool_reply(reply, POOL_STATUS_OK, length);
+}
+
+size_t orion_rx_pool_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len,
+                                uint8_t *reply, size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 8) {
+        return 0;
+    }
+    if (reply_capacity < 12) {
+        return pool_reply(reply, POOL_STATUS_EINVAL, 0);
+    }
+    if (request_len >= 4 && get_u32(request) == ORION_RX_POOL_OP_STATS) {
+        return pool_stats(reply, reply_capacity);
+    }
+    if (request_len < 8 || get_u32(request) != ORION_RX_POOL_OP_DELIVER) {
+        return pool_reply(reply, POOL_STATUS_EINVAL, 0);
+    }
+
+    uint32_t id = get_u32(request + 4);
+    if (id == 0 || id > ORION_RX_POOL_MAX_POOLS) {
+        return pool_reply(reply, POOL_STATUS_ENOENT, 0);
+    }
+
+    // The busy flag keeps the pool registered while it runs unlocked
+    int status = POOL_STATUS_OK;
+    rx_pool_t *pool = &pools[id - 1];
+    spinlock_acquire(&pool_lock);
+    if (!pool->base || pool->owner != sender) {
+        status = POOL_STATUS_ENOENT;
+    } else if (pool->busy) {
+        status = POOL_STATUS_EBUSY;
+    } else {
+        pool->busy = true;
+    }
+    spinlock_release(&pool_lock);
+    if (status != POOL_STATUS_OK) {
+        return pool_reply(reply, status, 0);
+    }
+
+    uint32_t handled = 0;
+    status = pool_process(pool, &handled);
+    uint32_t flags = pool->paused ? ORION_RX_POOL_DELIVER_BACKPRESSURE : 0;
+
+    spinlock_acquire(&pool_lock);
+    pool->busy = false;
+    spinlock_release(&pool_lock);
+
+    if (status != POOL_STATUS_OK && handled == 0) {
+        return pool_reply(reply, status, 0);
+    }
+    put_u32(reply + 4, handled);
+    put_u32(reply + 8, flags);
+    return pool_reply(reply, POOL_STATUS_OK, 8);
+}
+
+void orion_rx_pool_resume(void)
+{
+    for (uint32_t i = 0; i < ORION_RX_POOL_MAX_POOLS; i++) {
+        rx_pool_t *pool = &pools[i];
+        bool claimed = false;
+
+        // A pool being delivered is drained by its own DELIVER
+        spinlock_acquire(&pool_lock);
+        if (pool->base && pool->paused && !pool->busy) {
+            pool->busy = true;
+            claimed = true;
+        }
+        spinlock_release(&pool_lock);
+        if (!claimed) {
+            continue;
+        }
+
+        uint32_t handled = 0;
+        if (pool_process(pool, &handled) != POOL_STATUS_OK) {
+            klog_warning(KLOG_CAT_KERNEL, "Receive pool %u rings corrupted while resuming", i + 1);
+        }
+
+        spinlock_acquire(&pool_lock);
+        pool->busy = false;
+        spinlock_release(&pool_lock);
+    }
+}
+
+void orion_rx_pool_release(uint64_t owner, void (*unmap)(void *base))
+{
+    for (uint32_t i = 0; i < ORION_RX_POOL_MAX_POOLS; i++) {
+        void *base = orion_rx_pool_unregister(owner, i + 1);
+        if (base && unmap) {
+            unmap(base);
+        }
+    }
+}
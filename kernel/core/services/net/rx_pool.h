@@ -0,0 +1,110 @@
+/*
+ * Orion Operating System - Zero-Copy Receive Pools
+ *
+ * Receive buffer pools shared with NIC drivers, the network server side
+ * of lib/orion_rxpool. A driver lays out a pool in a shared memory object,
+ * points its DMA descriptors at pool buffers and passes filled buffers by
+ * index on the rx ring; the server parses each frame where the device
+ * wrote it and returns the buffer on the fill ring. Same framing as
+ * socket_ipc.h:
+ *
+ *   REGISTER    memory:u64  -> pool:u32
+ *   DELIVER     pool:u32    -> handled:u32 flags:u32
+ *   UNREGISTER  pool:u32    -> (empty)
+ *   STATS       (none)      -> count:u32 {pool:u32 node:u32
+ *                              buffer_count:u32 buffer_size:u32 queued:u32
+ *                              low:u32 high:u32 exhausted:u32
+ *                              shortfall:u32}*
+ *
+ * REGISTER and UNREGISTER map and unmap the region, so the message loop
+ * handles them with orion_rx_pool_register and orion_rx_pool_unregister;
+ * DELIVER and STATS go through orion_rx_pool_ipc_handle. Every frame
+ * queued on the rx ring has been processed and its buffer recycled before
+ * DELIVER replies, unless socket memory is under pressure: then the
+ * remaining frames stay queued, the reply carries
+ * ORION_RX_POOL_DELIVER_BACKPRESSURE and the driver stops reposting
+ * buffers until a DELIVER reply comes back without it.
+ *
+ * STATS lists every pool with the NUMA node, refill watermarks and
+ * exhaustion counts its driver published in the pool header, and the
+ * frames left queued on its rx ring.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_RX_POOL_H
+#define ORION_RX_POOL_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_RX_POOL_OP_REGISTER 0x110
+#define ORION_RX_POOL_OP_DELIVER 0x111
+#define ORION_RX_POOL_OP_UNREGISTER 0x112
+#define ORION_RX_POOL_OP_STATS 0x113
+
+#define ORION_RX_POOL_MAX_POOLS 32        // Pools across all drivers
+#define ORION_RX_POOL_MAX_PER_PROCESS 8   // Pools one driver may register, one per queue
+#define ORION_RX_POOL_MAX_BUFFERS 4096    // Largest pool accepted
+#define ORION_RX_POOL_MAX_BUFFER_SIZE 16384
+
+#define ORION_RX_POOL_DELIVER_BACKPRESSURE 0x1 // Frames left queued, stop reposting
+
+#define ORION_RX_POOL_RECORD_SIZE 36
+#define ORION_RX_POOL_NODE_ANY 0xFFFFFFFFU // Pool memory not placed on a given node
+
+    /**
+     * @brief Take over a pool region mapped for a driver
+     * @param owner Process that registered the pool
+     * @param base Address the region is mapped at (8-byte aligned)
+     * @param size Region size
+     * @param id Pool identifier (output)
+     * @return 0 or a negative errno
+     */
+    int orion_rx_pool_register(uint64_t owner, void *base, size_t size, uint32_t *id);
+
+    /**
+     * @brief Forget a pool
+     * @param owner Process that registered the pool
+     * @param id Pool identifier
+     * @return Region to unmap, or NULL if the pool is unknown or busy
+     */
+    void *orion_rx_pool_unregister(uint64_t owner, uint32_t id);
+
+    /**
+     * @brief Handle one DELIVER or STATS request
+     * @param sender Process that sent the request
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_rx_pool_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len,
+                                    uint8_t *reply, size_t reply_capacity);
+
+    /**
+     * @brief Drain the pools paused under socket memory pressure
+     */
+    void orion_rx_pool_resume(void);
+
+    /**
+     * @brief Drop every pool of an exiting driver
+     * @param owner Process identifier
+     * @param unmap Called with each region to unmap
+     */
+    void orion_rx_pool_release(uint64_t owner, void (*unmap)(void *base));
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_RX_POOL_H
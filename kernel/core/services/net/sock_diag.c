@@ -0,0 +1,233 @@
+/*
+ * Orion Operating System - Socket Diagnostics Implementation
+ *
+ * Sockets are copied out of the stack in small batches and filtered
+ * here, so the stack locks are never held while a reply is written.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "sock_diag.h"
+#include "netns.h"
+#include "tcp_ip_stack.h"
+#include <orion/string.h>
+#include <string.h>
+
+#define DIAG_STATUS_OK 0
+#define DIAG_STATUS_EINVAL -22
+
+#define DIAG_BATCH 16
+
+_Static_assert(sizeof(orion_tcpip_stats_t) == ORION_SOCK_DIAG_COUNTERS * sizeof(uint64_t),
+               "SUMMARY carries every counter of orion_tcpip_stats_t");
+
+typedef struct {
+    uint8_t protocols;
+    uint16_t port;
+    uint32_t states;
+    uint32_t ns_address; // Namespace address of the sender, 0 on the host
+} diag_filter_t;
+
+static uint32_t get_u16(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8);
+}
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static void put_u16(uint8_t *p, uint16_t v)
+{
+    p[0] = (uint8_t)v;
+    p[1] = (uint8_t)(v >> 8);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+static void put_u64(uint8_t *p, uint64_t v)
+{
+    put_u32(p, (uint32_t)v);
+    put_u32(p + 4, (uint32_t)(v >> 32));
+}
+
+static size_t diag_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+// Namespaces see their own sockets, the host everything else
+static bool diag_visible(const orion_sock_info_t *info, uint32_t ns_address)
+{
+    if (ns_address) {
+        return info->local_ip == ns_address;
+    }
+    return info->local_ip == 0 || !orion_netns_owns_address(info->local_ip);
+}
+
+static bool diag_matches(const orion_sock_info_t *info, const diag_filter_t *filter)
+{
+    uint8_t protocol = info->protocol == ORION_IP_PROTOCOL_TCP ? ORION_SOCK_DIAG_TCP : ORION_SOCK_DIAG_UDP;
+    if (filter->protocols && !(filter->protocols & protocol)) {
+        return false;
+    }
+    if (filter->states &&
+        (protocol != ORION_SOCK_DIAG_TCP || info->state >= 32 || !(filter->states & (1U << info->state)))) {
+        return false;
+    }
+    if (filter->port && info->local_port != filter->port && info->remote_port != filter->port) {
+        return false;
+    }
+    return diag_visible(info, filter->ns_address);
+}
+
+static void diag_put_entry(uint8_t *entry, const orion_sock_info_t *info)
+{
+    entry[0] = info->protocol;
+    entry[1] = info->state;
+    entry[2] = info->congestion_control;
+    entry[3] = 0;
+    put_u32(entry + 4, info->local_ip);
+    put_u32(entry + 8, info->remote_ip);
+    put_u16(entry + 12, info->local_port);
+    put_u16(entry + 14, info->remote_port);
+    put_u32(entry + 16, info->recv_queue);
+    put_u32(entry + 20, info->send_queue);
+    put_u32(entry + 24, info->in_flight);
+    put_u32(entry + 28, info->retransmissions);
+    put_u32(entry + 32, info->timeouts);
+    put_u32(entry + 36, info->cwnd);
+    put_u32(entry + 40, info->ssthresh);
+    put_u32(entry + 44, info->rtt_us);
+    put_u32(entry + 48, info->rttvar_us);
+    put_u32(entry + 52, info->rto_ms);
+    put_u64(entry + 56, info->bytes_sent);
+    put_u64(entry + 64, info->bytes_received);
+    put_u64(entry + 72, info->dropped);
+}
+
+static size_t diag_list(const diag_filter_t *filter, uint32_t cursor, uint8_t *reply, size_t reply_capacity)
+{
+    size_t room = (reply_capacity - 12) / ORION_SOCK_DIAG_ENTRY_SIZE;
+    uint8_t *entries = reply + 12;
+    uint32_t count = 0;
+    uint32_t next = cursor;
+    orion_sock_info_t batch[DIAG_BATCH];
+
+    bool full = false;
+    while (!full) {
+        size_t copied = orion_tcpip_sock_info(next, batch, DIAG_BATCH);
+        for (size_t i = 0; i < copied && !full; i++) {
+            if (!diag_matches(&batch[i], filter)) {
+                next++;
+            } else if (count == room) {
+                // Resume with this socket
+                full = true;
+            } else {
+                diag_put_entry(entries + (size_t)count * ORION_SOCK_DIAG_ENTRY_SIZE, &batch[i]);
+                count++;
+                next++;
+            }
+        }
+        if (!full && copied < DIAG_BATCH) {
+            next = 0;
+            break;
+        }
+    }
+
+    put_u32(reply + 4, next);
+    put_u32(reply + 8, count);
+    return diag_reply(reply, DIAG_STATUS_OK, 8 + (size_t)count * ORION_SOCK_DIAG_ENTRY_SIZE);
+}
+
+static size_t diag_summary(uint32_t ns_address, uint8_t *reply)
+{
+    uint32_t tcp_sockets = 0;
+    uint32_t udp_sockets = 0;
+    orion_sock_info_t batch[DIAG_BATCH];
+    size_t position = 0;
+    for (;;) {
+        size_t copied = orion_tcpip_sock_info(position, batch, DIAG_BATCH);
+        for (size_t i = 0; i < copied; i++) {
+            if (!diag_visible(&batch[i], ns_address)) {
+                continue;
+            }
+            if (batch[i].protocol == ORION_IP_PROTOCOL_TCP) {
+                tcp_sockets++;
+            } else {
+                udp_sockets++;
+            }
+        }
+        position += copied;
+        if (copied < DIAG_BATCH) {
+            break;
+        }
+    }
+
+    orion_tcpip_stats_t stats;
+    uint32_t established = 0;
+    orion_tcpip_get_stats(&stats, &established);
+
+    put_u32(reply + 4, tcp_sockets);
+    put_u32(reply + 8, udp_sockets);
+    put_u32(reply + 12, established);
+    put_u32(reply + 16, 0);
+    const uint64_t *counters = (const uint64_t *)&stats;
+    for (size_t i = 0; i < ORION_SOCK_DIAG_COUNTERS; i++) {
+        put_u64(reply + 20 + i * 8, counters[i]);
+    }
+    return diag_reply(reply, DIAG_STATUS_OK, 16 + ORION_SOCK_DIAG_COUNTERS * 8);
+}
+
+size_t orion_sock_diag_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len, uint8_t *reply,
+                                  size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 20 + ORION_SOCK_DIAG_COUNTERS * 8) {
+        return 0;
+    }
+    if (request_len < 4) {
+        return diag_reply(reply, DIAG_STATUS_EINVAL, 0);
+    }
+
+    uint32_t ns_address = 0;
+    uint32_t ns = orion_netns_of(sender);
+    if (ns != 0 && orion_netns_address(ns, &ns_address) != 0) {
+        return diag_reply(reply, DIAG_STATUS_EINVAL, 0);
+    }
+
+    uint32_t op = get_u32(request);
+    const uint8_t *args = request + 4;
+    size_t args_len = request_len - 4;
+
+    switch (op) {
+    case ORION_SOCK_DIAG_OP_LIST: {
+        if (args_len < 12) {
+            return diag_reply(reply, DIAG_STATUS_EINVAL, 0);
+        }
+        diag_filter_t filter = {
+            .protocols = args[0],
+            .port = (uint16_t)get_u16(args + 2),
+            .states = get_u32(args + 4),
+            .ns_address = ns_address,
+        };
+        return diag_list(&filter, get_u32(args + 8), reply, reply_capacity);
+    }
+
+    case ORION_SOCK_DIAG_OP_SUMMARY:
+        return diag_summary(ns_address, reply);
+
+    default:
+        return diag_reply(reply, DIAG_STATUS_EINVAL, 0);
+    }
+}
@@ -0,0 +1,73 @@
+/*
+ * Orion Operating System - Socket Diagnostics
+ *
+ * Read-only view of the sockets of the network server and its
+ * stack-wide counters, as orion-ss shows them. Same framing as
+ * socket_ipc.h; opcodes start at 40:
+ *
+ *   LIST     protocols:u8 pad:u8 port:u16 states:u32 cursor:u32
+ *                                   -> next:u32 count:u32 entry[count]
+ *   SUMMARY  (empty)                -> tcp_sockets:u32 udp_sockets:u32
+ *                                      tcp_established:u32 pad:u32
+ *                                      counters:u64[ORION_SOCK_DIAG_COUNTERS]
+ *
+ * LIST filters by protocol (ORION_SOCK_DIAG_TCP/UDP bits, 0 for both),
+ * by TCP state (bit 1 << orion_tcp_state_t, 0 for any; a state filter
+ * leaves UDP endpoints out) and by port (local or remote, 0 for any).
+ * Start with cursor 0 and call again with `next` until it comes back 0;
+ * a reply holds as many entries as fit. Each entry is
+ * ORION_SOCK_DIAG_ENTRY_SIZE bytes:
+ *
+ *   protocol:u8 state:u8 congestion_control:u8 pad:u8
+ *   local_ip:u32 remote_ip:u32 local_port:u16 remote_port:u16
+ *   recv_queue:u32 send_queue:u32 in_flight:u32 retransmissions:u32
+ *   timeouts:u32 cwnd:u32 ssthresh:u32 rtt_us:u32 rttvar_us:u32
+ *   rto_ms:u32 bytes_sent:u64 bytes_received:u64 dropped:u64
+ *
+ * The SUMMARY counters follow the order of orion_tcpip_stats_t. A
+ * sender in a network namespace only sees the sockets bound to the
+ * namespace address; others see the rest.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_NET_SOCK_DIAG_H
+#define ORION_NET_SOCK_DIAG_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_SOCK_DIAG_OP_LIST 40
+#define ORION_SOCK_DIAG_OP_SUMMARY 41
+
+#define ORION_SOCK_DIAG_TCP (1U << 0)
+#define ORION_SOCK_DIAG_UDP (1U << 1)
+
+#define ORION_SOCK_DIAG_ENTRY_SIZE 80
+#define ORION_SOCK_DIAG_COUNTERS 17
+
+    /**
+     * @brief Handle one socket diagnostics request
+     * @param sender PID of the sender
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_sock_diag_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len, uint8_t *reply,
+                                      size_t reply_capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_NET_SOCK_DIAG_H
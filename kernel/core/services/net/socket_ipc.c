@@ -0,0 +1,610 @@
+/*
+ * Orion Operating System - Socket IPC Interface Implementation
+ *
+ * Maps socket identifiers handed to client processes onto TCP connections
+ * and UDP endpoints of the stack. TLS termination configured with
+ * LISTEN_TLS or START_TLS is transparent to clients: SEND and RECV carry
+ * plaintext. While any socket is open, the devices of the NIC drivers
+ * are held at the I/O server (device_hold.h).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "socket_ipc.h"
+#include "device_hold.h"
+#include "tcp_ip_stack.h"
+#include "netns.h"
+#include "ping.h"
+#include <orion/mac.h>
+#include <orion/string.h>
+#include <orion/spinlock.h>
+#include <string.h>
+
+#define SOCKET_STATUS_OK 0
+#define SOCKET_STATUS_EBADF -9
+#define SOCKET_STATUS_EAGAIN -11
+#define SOCKET_STATUS_ENOMEM -12
+#define SOCKET_STATUS_EACCES -13
+#define SOCKET_STATUS_EINVAL -22
+#define SOCKET_STATUS_EMFILE -24
+#define SOCKET_STATUS_EPIPE -32
+#define SOCKET_STATUS_ENOPROTOOPT -92
+#define SOCKET_STATUS_EMSGSIZE -90
+#define SOCKET_STATUS_EADDRINUSE -98
+#define SOCKET_STATUS_EADDRNOTAVAIL -99
+
+// A slot holds either a TCP connection or a UDP endpoint
+static struct {
+    orion_tcp_connection_t *conn;
+    orion_udp_endpoint_t *udp;
+    uint64_t owner;
+} socket_table[ORION_SOCKET_MAX_SOCKETS];
+
+// Slots in use
+static uint32_t socket_open = 0;
+
+static spinlock_t socket_lock = SPINLOCK_INITIALIZER;
+
+// Local ports of outgoing streams, cycling through the IANA dynamic range
+#define SOCKET_EPHEMERAL_FIRST 49152
+static uint32_t next_ephemeral_port = SOCKET_EPHEMERAL_FIRST;
+
+static uint32_t get_u16(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8);
+}
+
+static uint32_t get_u32(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static uint64_t get_u64(const uint8_t *p)
+{
+    return (uint64_t)get_u32(p) | ((uint64_t)get_u32(p + 4) << 32);
+}
+
+static void put_u16(uint8_t *p, uint16_t v)
+{
+    p[0] = (uint8_t)v;
+    p[1] = (uint8_t)(v >> 8);
+}
+
+static void put_u32(uint8_t *p, uint32_t v)
+{
+    for (int i = 0; i < 4; i++) {
+        p[i] = (uint8_t)(v >> (8 * i));
+    }
+}
+
+/* ============================================================================
+ * Socket Table
+ * ============================================================================ */
+
+// Socket identifiers are table index + 1 so that 0 is never valid
+static int socket_insert(orion_tcp_connection_t *conn, orion_udp_endpoint_t *udp, uint64_t owner,
+                         uint32_t *id)
+{
+    for (uint32_t i = 0; i < ORION_SOCKET_MAX_SOCKETS; i++) {
+        if (!socket_table[i].conn && !socket_table[i].udp) {
+            socket_table[i].conn = conn;
+            socket_table[i].udp = udp;
+            socket_table[i].owner = owner;
+            socket_open++;
+            *id = i + 1;
+            return 0;
+        }
+    }
+    return SOCKET_STATUS_EMFILE;
+}
+
+static orion_tcp_connection_t *socket_lookup(uint32_t id, uint64_t owner)
+{
+    if (id == 0 || id > ORION_SOCKET_MAX_SOCKETS) {
+        return NULL;
+    }
+    if (socket_table[id - 1].owner != owner) {
+        return NULL;
+    }
+    return socket_table[id - 1].conn;
+}
+
+static orion_udp_endpoint_t *socket_lookup_udp(uint32_t id, uint64_t owner)
+{
+    if (id == 0 || id > ORION_SOCKET_MAX_SOCKETS) {
+        return NULL;
+    }
+    if (socket_table[id - 1].owner != owner) {
+        return NULL;
+    }
+    return socket_table[id - 1].udp;
+}
+
+/* ============================================================================
+ * Request Handling
+ * ============================================================================ */
+
+static size_t socket_reply(uint8_t *reply, int32_t status, size_t payload_len)
+{
+    put_u32(reply, (uint32_t)status);
+    return 4 + payload_len;
+}
+
+// Local address a sender may use for `requested`: anything on the host,
+// only its own address in a network namespace, where the wildcard stands
+// for it. *local is left as requested for the host
+static int socket_local_address(uint64_t sender, uint32_t requested, uint32_t *local)
+{
+    *local = requested;
+    uint32_t ns = orion_netns_of(sender);
+    if (ns == 0) {
+        return SOCKET_STATUS_OK;
+    }
+    uint32_t address = 0;
+    if (orion_netns_address(ns, &address) != 0) {
+        return SOCKET_STATUS_ENOMEM;
+    }
+    if (requested != 0 && requested != address) {
+        return SOCKET_STATUS_EADDRNOTAVAIL;
+    }
+    *local = address;
+    return SOCKET_STATUS_OK;
+}
+
+// Whether the MAC policy lets `sender` use `port`, named "tcp:<port>" or
+// "udp:<port>" for it (see mac.h)
+static int socket_mac_check(uint64_t sender, const char *protocol, uint16_t port, uint32_t perms)
+{
+    char name[16];
+    snprintf(name, sizeof(name), "%s:%u", protocol, (unsigned)port);
+    return mac_check(sender, MAC_CLASS_SOCKET, name, perms) == 0 ? SOCKET_STATUS_OK : SOCKET_STATUS_EACCES;
+}
+
+// Socket slot release shared by CLOSE on either kind of socket: only the
+// request that clears the slot may free what it pointed to
+static bool socket_clear(uint32_t id, orion_tcp_connection_t *conn, orion_udp_endpoint_t *udp)
+{
+    spinlock_acquire(&socket_lock);
+    bool owned = socket_table[id - 1].conn == conn && socket_table[id - 1].udp == udp;
+    if (owned) {
+        socket_table[id - 1].conn = NULL;
+        socket_table[id - 1].udp = NULL;
+        socket_table[id - 1].owner = 0;
+        socket_open--;
+    }
+    spinlock_release(&socket_lock);
+    return owned;
+}
+
+static size_t socket_handle_udp(uint32_t op, uint32_t id, orion_udp_endpoint_t *udp, const uint8_t *args,
+                                size_t args_len, uint8_t *reply, size_t reply_capacity)
+{
+    switch (op) {
+    case ORION_SOCKET_OP_SENDTO: {
+        if (args_len < 10) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        size_t len = args_len - 10;
+        if (len > ORION_UDP_MAX_PAYLOAD) {
+            return socket_reply(reply, SOCKET_STATUS_EMSGSIZE, 0);
+        }
+        int result = orion_udp_sendto(udp, get_u32(args + 4), (uint16_t)get_u16(args + 8), args + 10, len);
+        if (result == ORION_IP_ERR_MSGSIZE) {
+            return socket_reply(reply, SOCKET_STATUS_EMSGSIZE, 0);
+        }
+        if (result != 0) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        return socket_reply(reply, SOCKET_STATUS_OK, 0);
+    }
+
+    case ORION_SOCKET_OP_RECVERR: {
+        if (reply_capacity < 20) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        orion_udp_error_t error;
+        if (orion_udp_recverr(udp, &error) <= 0) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        put_u32(reply + 4, error.reporter);
+        reply[8] = error.type;
+        reply[9] = error.code;
+        put_u16(reply + 10, error.mtu);
+        put_u32(reply + 12, error.dst_ip);
+        put_u16(reply + 16, error.dst_port);
+        return socket_reply(reply, SOCKET_STATUS_OK, 14);
+    }
+
+    case ORION_SOCKET_OP_RECVFROM: {
+        if (args_len < 8 || reply_capacity < 10) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        size_t max = get_u32(args + 4);
+        if (max > reply_capacity - 10) {
+            max = reply_capacity - 10;
+        }
+
+        uint32_t src_ip = 0;
+        uint16_t src_port = 0;
+        ssize_t received = orion_udp_recvfrom(udp, &src_ip, &src_port, reply + 10, max);
+        if (received <= 0) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        put_u32(reply + 4, src_ip);
+        put_u16(reply + 8, src_port);
+        return socket_reply(reply, SOCKET_STATUS_OK, 6 + (size_t)received);
+    }
+
+    case ORION_SOCKET_OP_SETSOCKOPT: {
+        if (args_len < 12) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        uint32_t value = get_u32(args + 8);
+        switch (get_u32(args + 4)) {
+        case ORION_SOCKET_OPT_ADD_MEMBERSHIP:
+        case ORION_SOCKET_OPT_DROP_MEMBERSHIP: {
+            if (args_len < 16) {
+                return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+            }
+            int result = get_u32(args + 4) == ORION_SOCKET_OPT_ADD_MEMBERSHIP
+                             ? orion_udp_join_group(udp, value, get_u32(args + 12))
+                             : orion_udp_leave_group(udp, value, get_u32(args + 12));
+            return socket_reply(reply, result == 0 ? SOCKET_STATUS_OK : SOCKET_STATUS_EINVAL, 0);
+        }
+        case ORION_SOCKET_OPT_MULTICAST_LOOP:
+            orion_udp_set_multicast_loop(udp, value != 0);
+            return socket_reply(reply, SOCKET_STATUS_OK, 0);
+        case ORION_SOCKET_OPT_MULTICAST_TTL:
+            if (value > 255) {
+                return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+            }
+            orion_udp_set_multicast_ttl(udp, (uint8_t)value);
+            return socket_reply(reply, SOCKET_STATUS_OK, 0);
+        case ORION_SOCKET_OPT_MULTICAST_IF:
+            orion_udp_set_multicast_if(udp, value);
+            return socket_reply(reply, SOCKET_STATUS_OK, 0);
+        case ORION_SOCKET_OPT_TTL:
+            if (value == 0 || value > 255) {
+                return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+            }
+            orion_udp_set_ttl(udp, (uint8_t)value);
+            return socket_reply(reply, SOCKET_STATUS_OK, 0);
+        case ORION_SOCKET_OPT_DONT_FRAGMENT:
+            orion_udp_set_dont_fragment(udp, value != 0);
+            return socket_reply(reply, SOCKET_STATUS_OK, 0);
+        default:
+            return socket_reply(reply, SOCKET_STATUS_ENOPROTOOPT, 0);
+        }
+    }
+
+    case ORION_SOCKET_OP_CLOSE:
+        if (!socket_clear(id, NULL, udp)) {
+            return socket_reply(reply, SOCKET_STATUS_EBADF, 0);
+        }
+        orion_udp_close(udp);
+        return socket_reply(reply, SOCKET_STATUS_OK, 0);
+
+    default:
+        return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+    }
+}
+
+// Sockets open, told to device_hold.c after each change
+static uint32_t socket_open_count(void)
+{
+    spinlock_acquire(&socket_lock);
+    uint32_t open = socket_open;
+    spinlock_release(&socket_lock);
+    return open;
+}
+
+static size_t socket_dispatch(uint64_t sender, const uint8_t *request, size_t request_len, uint8_t *reply,
+                              size_t reply_capacity)
+{
+    if (!request || !reply || reply_capacity < 8) {
+        return 0;
+    }
+    if (request_len < 8) {
+        return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+    }
+
+    uint32_t op = get_u32(request);
+    const uint8_t *args = request + 4;
+    size_t args_len = request_len - 4;
+
+    if (op == ORION_SOCKET_OP_LISTEN) {
+        if (args_len < 8) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        int mac_status = socket_mac_check(sender, "tcp", (uint16_t)get_u16(args + 4), MAC_PERM_BIND);
+        if (mac_status != SOCKET_STATUS_OK) {
+            return socket_reply(reply, mac_status, 0);
+        }
+        uint32_t local_ip = 0;
+        int local_status = socket_local_address(sender, get_u32(args), &local_ip);
+        if (local_status != SOCKET_STATUS_OK) {
+            return socket_reply(reply, local_status, 0);
+        }
+        orion_tcp_connection_t *listener = orion_tcp_listen(local_ip, (uint16_t)get_u16(args + 4),
+                                                            (int)get_u16(args + 6));
+        if (!listener) {
+            return socket_reply(reply, SOCKET_STATUS_ENOMEM, 0);
+        }
+
+        uint32_t id = 0;
+        spinlock_acquire(&socket_lock);
+        int status = socket_insert(listener, NULL, sender, &id);
+        spinlock_release(&socket_lock);
+        if (status != 0) {
+            orion_tcp_close(listener);
+            return socket_reply(reply, status, 0);
+        }
+        put_u32(reply + 4, id);
+        return socket_reply(reply, SOCKET_STATUS_OK, 4);
+    }
+
+    if (op == ORION_SOCKET_OP_CONNECT || op == ORION_SOCKET_OP_CONNECT_TLS) {
+        bool tls = op == ORION_SOCKET_OP_CONNECT_TLS;
+        char server_name[ORION_TLS_MAX_SERVER_NAME + 1];
+        if (args_len < (tls ? 15u : 6u) || (tls && args_len - 14 > ORION_TLS_MAX_SERVER_NAME)) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        int mac_status = socket_mac_check(sender, "tcp", (uint16_t)get_u16(args + 4), MAC_PERM_CONNECT);
+        if (mac_status != SOCKET_STATUS_OK) {
+            return socket_reply(reply, mac_status, 0);
+        }
+        // Streams of a namespace leave from its address
+        uint32_t local_ip = 0;
+        int local_status = socket_local_address(sender, 0, &local_ip);
+        if (local_status != SOCKET_STATUS_OK) {
+            return socket_reply(reply, local_status, 0);
+        }
+        spinlock_acquire(&socket_lock);
+        uint16_t local_port = (uint16_t)next_ephemeral_port;
+        next_ephemeral_port = next_ephemeral_port == 65535 ? SOCKET_EPHEMERAL_FIRST : next_ephemeral_port + 1;
+        spinlock_release(&socket_lock);
+
+        orion_tcp_connection_t *stream = orion_tcp_connect(local_ip, local_port, get_u32(args), (uint16_t)get_u16(args + 4));
+        if (!stream) {
+            return socket_reply(reply, SOCKET_STATUS_ENOMEM, 0);
+        }
+        if (tls) {
+            memcpy(server_name, args + 14, args_len - 14);
+            server_name[args_len - 14] = '\0';
+            if (orion_tcp_start_tls_client(stream, get_u64(args + 6), server_name) != 0) {
+                orion_tcp_close(stream);
+                return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+            }
+        }
+
+        uint32_t id = 0;
+        spinlock_acquire(&socket_lock);
+        int status = socket_insert(stream, NULL, sender, &id);
+        spinlock_release(&socket_lock);
+        if (status != 0) {
+            orion_tcp_close(stream);
+            return socket_reply(reply, status, 0);
+        }
+        put_u32(reply + 4, id);
+        return socket_reply(reply, SOCKET_STATUS_OK, 4);
+    }
+
+    if (op == ORION_SOCKET_OP_UDP_BIND) {
+        if (args_len < 6) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        int mac_status = socket_mac_check(sender, "udp", (uint16_t)get_u16(args + 4), MAC_PERM_BIND);
+        if (mac_status != SOCKET_STATUS_OK) {
+            return socket_reply(reply, mac_status, 0);
+        }
+        uint32_t local_ip = 0;
+        int local_status = socket_local_address(sender, get_u32(args), &local_ip);
+        if (local_status != SOCKET_STATUS_OK) {
+            return socket_reply(reply, local_status, 0);
+        }
+        orion_udp_endpoint_t *endpoint = orion_udp_bind(local_ip, (uint16_t)get_u16(args + 4));
+        if (!endpoint) {
+            return socket_reply(reply, SOCKET_STATUS_EADDRINUSE, 0);
+        }
+
+        uint32_t id = 0;
+        spinlock_acquire(&socket_lock);
+        int status = socket_insert(NULL, endpoint, sender, &id);
+        spinlock_release(&socket_lock);
+        if (status != 0) {
+            orion_udp_close(endpoint);
+            return socket_reply(reply, status, 0);
+        }
+        put_u32(reply + 4, id);
+        put_u16(reply + 8, orion_udp_local_port(endpoint));
+        return socket_reply(reply, SOCKET_STATUS_OK, 6);
+    }
+
+    spinlock_acquire(&socket_lock);
+    uint32_t id = get_u32(args);
+    orion_tcp_connection_t *conn = socket_lookup(id, sender);
+    orion_udp_endpoint_t *udp = socket_lookup_udp(id, sender);
+    spinlock_release(&socket_lock);
+
+    if (udp) {
+        return socket_handle_udp(op, id, udp, args, args_len, reply, reply_capacity);
+    }
+    if (!conn) {
+        return socket_reply(reply, SOCKET_STATUS_EBADF, 0);
+    }
+
+    switch (op) {
+    case ORION_SOCKET_OP_LISTEN_TLS:
+        if (args_len < 20) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        if (orion_tcp_listen_tls(conn, get_u64(args + 4), get_u64(args + 12)) != 0) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        return socket_reply(reply, SOCKET_STATUS_OK, 0);
+
+    case ORION_SOCKET_OP_START_TLS:
+        if (args_len < 20) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        if (orion_tcp_start_tls(conn, get_u64(args + 4), get_u64(args + 12)) != 0) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        return socket_reply(reply, SOCKET_STATUS_OK, 0);
+
+    case ORION_SOCKET_OP_ACCEPT: {
+        if (orion_tcp_get_state(conn) != ORION_TCP_STATE_LISTEN) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        orion_tcp_connection_t *accepted = orion_tcp_accept(conn);
+        if (!accepted) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+
+        uint32_t accepted_id = 0;
+        spinlock_acquire(&socket_lock);
+        int status = socket_insert(accepted, NULL, sender, &accepted_id);
+        spinlock_release(&socket_lock);
+        if (status != 0) {
+            orion_tcp_close(accepted);
+            return socket_reply(reply, status, 0);
+        }
+        put_u32(reply + 4, accepted_id);
+        return socket_reply(reply, SOCKET_STATUS_OK, 4);
+    }
+
+    case ORION_SOCKET_OP_SEND: {
+        if (orion_tcp_get_state(conn) == ORION_TCP_STATE_SYN_SENT) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        size_t len = args_len - 4;
+        if (len > ORION_SOCKET_MAX_TRANSFER) {
+            len = ORION_SOCKET_MAX_TRANSFER;
+        }
+        // A full send buffer is a short write, not an error
+        size_t space = conn->send_buffer_size - conn->send_buffer_used;
+        if (!conn->tls_session && len > space) {
+            len = space;
+        }
+        if (len == 0 && args_len > 4) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        ssize_t sent = len ? orion_tcp_send(conn, args + 4, len) : 0;
+        if (sent < 0) {
+            return socket_reply(reply, SOCKET_STATUS_EPIPE, 0);
+        }
+        if (sent == 0 && len > 0) {
+            // TLS handshake still running or its output queue full
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        put_u32(reply + 4, (uint32_t)sent);
+        return socket_reply(reply, SOCKET_STATUS_OK, 4);
+    }
+
+    case ORION_SOCKET_OP_RECV: {
+        if (args_len < 8) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        size_t max = get_u32(args + 4);
+        if (max > reply_capacity - 4) {
+            max = reply_capacity - 4;
+        }
+        if (max > ORION_SOCKET_MAX_TRANSFER) {
+            max = ORION_SOCKET_MAX_TRANSFER;
+        }
+        if (orion_tcp_get_state(conn) == ORION_TCP_STATE_SYN_SENT) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+
+        ssize_t received = orion_tcp_recv(conn, reply + 4, max);
+        if (received < 0) {
+            return socket_reply(reply, SOCKET_STATUS_EPIPE, 0);
+        }
+        if (received == 0) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        return socket_reply(reply, SOCKET_STATUS_OK, (size_t)received);
+    }
+
+    case ORION_SOCKET_OP_TLS_EXPORT: {
+        char label[ORION_TLS_MAX_EXPORTER_LABEL + 1];
+        size_t label_len = args_len > 6 ? args_len - 6 : 0;
+        size_t length = args_len >= 6 ? get_u16(args + 4) : 0;
+        if (label_len == 0 || label_len > ORION_TLS_MAX_EXPORTER_LABEL || length == 0 ||
+            length > ORION_TLS_MAX_EXPORT || reply_capacity < 4 + length) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        if (!conn->tls_listener && !conn->tls_session) {
+            return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+        }
+        if (orion_tcp_get_state(conn) == ORION_TCP_STATE_SYN_SENT) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        memcpy(label, args + 6, label_len);
+        label[label_len] = '\0';
+
+        int exported = orion_tcp_tls_export(conn, label, reply + 4, length);
+        if (exported > 0) {
+            return socket_reply(reply, SOCKET_STATUS_EAGAIN, 0);
+        }
+        if (exported < 0) {
+            return socket_reply(reply, SOCKET_STATUS_EPIPE, 0);
+        }
+        return socket_reply(reply, SOCKET_STATUS_OK, length);
+    }
+
+    case ORION_SOCKET_OP_CLOSE: {
+        if (!socket_clear(id, conn, NULL)) {
+            return socket_reply(reply, SOCKET_STATUS_EBADF, 0);
+        }
+        orion_tcp_close(conn);
+        return socket_reply(reply, SOCKET_STATUS_OK, 0);
+    }
+
+    default:
+        return socket_reply(reply, SOCKET_STATUS_EINVAL, 0);
+    }
+}
+
+size_t orion_socket_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len,
+                               uint8_t *reply, size_t reply_capacity)
+{
+    size_t length = socket_dispatch(sender, request, request_len, reply, reply_capacity);
+    orion_device_hold_sockets(socket_open_count());
+    return length;
+}
+
+void orion_socket_ipc_release(uint64_t owner)
+{
+    for (uint32_t i = 0; i < ORION_SOCKET_MAX_SOCKETS; i++) {
+        spinlock_acquire(&socket_lock);
+        orion_tcp_connection_t *conn = NULL;
+        orion_udp_endpoint_t *udp = NULL;
+        if ((socket_table[i].conn || socket_table[i].udp) && socket_table[i].owner == owner) {
+            conn = socket_table[i].conn;
+            udp = socket_table[i].udp;
+            socket_table[i].conn = NULL;
+            socket_table[i].udp = NULL;
+            socket_table[i].owner = 0;
+            socket_open--;
+        }
+        spinlock_release(&socket_lock);
+
+        if (conn) {
+            orion_tcp_close(conn);
+        }
+        if (udp) {
+            orion_udp_close(udp);
+        }
+    }
+
+    orion_ping_release(owner);
+    orion_device_hold_sockets(socket_open_count());
+
+    // The owner may have been the last process of its network namespace
+    orion_netns_prune();
+}
@@ -0,0 +1,124 @@
+/*
+ * Orion Operating System - Socket IPC Interface
+ *
+ * Socket operations offered by the network server to other
+ * processes. Requests and replies use the little-endian layout shared by
+ * the Rust services: a 32-bit opcode first, replies start with a 32-bit
+ * signed status (0 or a negative errno) followed by the payload.
+ *
+ *   LISTEN      ip:u32 port:u16 backlog:u16        -> socket:u32
+ *   LISTEN_TLS  socket:u32 cert:u64 key:u64        -> (empty)
+ *   ACCEPT      socket:u32                         -> socket:u32 or -EAGAIN
+ *   SEND        socket:u32 data...                 -> accepted:u32
+ *   RECV        socket:u32 max:u32                 -> data or -EAGAIN
+ *   CLOSE       socket:u32                         -> (empty)
+ *   UDP_BIND    ip:u32 port:u16                    -> socket:u32 port:u16
+ *   SENDTO      socket:u32 ip:u32 port:u16 data... -> (empty)
+ *   RECVFROM    socket:u32 max:u32                 -> ip:u32 port:u16 data
+ *                                                     or -EAGAIN
+ *   START_TLS   socket:u32 cert:u64 key:u64        -> (empty)
+ *   SETSOCKOPT  socket:u32 option:u32 value...     -> (empty)
+ *   CONNECT     ip:u32 port:u16                    -> socket:u32
+ *   CONNECT_TLS ip:u32 port:u16 anchor:u64 name... -> socket:u32
+ *   RECVERR     socket:u32                         -> reporter:u32 type:u8
+ *                                                     code:u8 mtu:u16 ip:u32
+ *                                                     port:u16 or -EAGAIN
+ *   TLS_EXPORT  socket:u32 length:u16 label...     -> keying material
+ *                                                     or -EAGAIN
+ *
+ * RECV answers -EPIPE once the peer closed the stream and everything was
+ * read. UDP_BIND with port 0 picks an ephemeral port; RECVFROM returns one
+ * datagram per call, truncated to `max`. START_TLS upgrades an accepted
+ * stream in the middle of a protocol (STARTTLS, VeNCrypt): data sent before
+ * it stays plaintext, the handshake starts with the next bytes received.
+ * SETSOCKOPT takes the IP_* multicast options of UDP sockets: ADD and
+ * DROP_MEMBERSHIP carry group:u32 ifaddr:u32 (0 for the default
+ * interface), LOOP, TTL and IF a single u32, as do TTL (unicast time to
+ * live) and DONT_FRAGMENT; other options answer -ENOPROTOOPT. With
+ * DONT_FRAGMENT set, SENDTO answers -EMSGSIZE for datagrams larger than
+ * the MTU of their route. RECVERR dequeues the ICMP errors (destination
+ * unreachable, time exceeded) quoting a datagram the UDP socket sent to
+ * ip:port, as traceroute and path MTU discovery need; `mtu` is the
+ * next-hop MTU of a fragmentation needed error. CONNECT opens a stream
+ * from an ephemeral port; SEND and RECV answer -EAGAIN until the
+ * handshake completes. CONNECT_TLS also runs the client side of TLS in
+ * the network server: the server has to
+ * present a chain leading to the CA certificate under keyring handle
+ * `anchor` and naming `name` (host name or dotted IPv4 address). SEND
+ * and RECV wait for the TLS handshake as well; once it failed they answer
+ * -EPIPE. TLS_EXPORT returns `length` bytes (at most 64) of keying
+ * material exported from the TLS session of a stream under `label` (at
+ * most 32 bytes) with an empty context, as RFC 8446 section 7.5 defines
+ * it; both ends of the connection compute the same value, and no other
+ * connection has it. It answers -EAGAIN while the handshake runs, -EINVAL
+ * on a stream without TLS and -EPIPE once the handshake failed.
+ * Sockets belong to the process that created or accepted them.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_SOCKET_IPC_H
+#define ORION_SOCKET_IPC_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_SOCKET_OP_LISTEN 1
+#define ORION_SOCKET_OP_LISTEN_TLS 2
+#define ORION_SOCKET_OP_ACCEPT 3
+#define ORION_SOCKET_OP_SEND 4
+#define ORION_SOCKET_OP_RECV 5
+#define ORION_SOCKET_OP_CLOSE 6
+#define ORION_SOCKET_OP_UDP_BIND 7
+#define ORION_SOCKET_OP_SENDTO 8
+#define ORION_SOCKET_OP_RECVFROM 9
+#define ORION_SOCKET_OP_START_TLS 10
+#define ORION_SOCKET_OP_SETSOCKOPT 11
+#define ORION_SOCKET_OP_CONNECT 12
+#define ORION_SOCKET_OP_CONNECT_TLS 13
+#define ORION_SOCKET_OP_RECVERR 14
+#define ORION_SOCKET_OP_TLS_EXPORT 15
+
+// SETSOCKOPT options, after IP_ADD_MEMBERSHIP and friends
+#define ORION_SOCKET_OPT_ADD_MEMBERSHIP 1
+#define ORION_SOCKET_OPT_DROP_MEMBERSHIP 2
+#define ORION_SOCKET_OPT_MULTICAST_LOOP 3
+#define ORION_SOCKET_OPT_MULTICAST_TTL 4
+#define ORION_SOCKET_OPT_MULTICAST_IF 5
+#define ORION_SOCKET_OPT_TTL 6
+#define ORION_SOCKET_OPT_DONT_FRAGMENT 7
+
+#define ORION_SOCKET_MAX_SOCKETS 256   // Sockets across all clients
+#define ORION_SOCKET_MAX_TRANSFER 8192 // Largest SEND/RECV payload
+
+    /**
+     * @brief Handle one socket request
+     * @param sender Process that sent the request
+     * @param request Request bytes
+     * @param request_len Request length
+     * @param reply Reply buffer (at least 4 + ORION_SOCKET_MAX_TRANSFER bytes)
+     * @param reply_capacity Reply buffer capacity
+     * @return Reply length
+     */
+    size_t orion_socket_ipc_handle(uint64_t sender, const uint8_t *request, size_t request_len,
+                                   uint8_t *reply, size_t reply_capacity);
+
+    /**
+     * @brief Close every socket owned by an exiting process
+     * @param owner Process identifier
+     */
+    void orion_socket_ipc_release(uint64_t owner);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_SOCKET_IPC_H
@@ -0,0 +1,129 @@
+/*
+ * Orion Operating System - Socket Buffer Memory Implementation
+ *
+ * The throttle has hysteresis: it engages when a charge takes usage past
+ * the pressure limit and lifts only once usage is back below min, so
+ * receive pools are not paused and resumed on every segment around the
+ * limit. Pools are resumed outside the accounting lock since resuming
+ * runs frames through the stack, which charges buffers again.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "socket_memory.h"
+#include "rx_pool.h"
+#include <orion/klog.h>
+#include <orion/spinlock.h>
+
+#define SOCKMEM_STATUS_OK 0
+#define SOCKMEM_STATUS_EINVAL -22
+
+static struct {
+    size_t min;
+    size_t pressure;
+    size_t max;
+    bool throttled;
+    orion_sockmem_stats_t stats;
+} sockmem = {
+    .min = ORION_SOCKMEM_DEFAULT_MIN,
+    .pressure = ORION_SOCKMEM_DEFAULT_PRESSURE,
+    .max = ORION_SOCKMEM_DEFAULT_MAX,
+};
+static spinlock_t sockmem_lock = SPINLOCK_INITIALIZER;
+
+int orion_sockmem_set_limits(size_t min, size_t pressure, size_t max)
+{
+    if (min > pressure || pressure > max || max == 0) {
+        return SOCKMEM_STATUS_EINVAL;
+    }
+
+    spinlock_acquire(&sockmem_lock);
+    sockmem.min = min;
+    sockmem.pressure = pressure;
+    sockmem.max = max;
+    spinlock_release(&sockmem_lock);
+    return SOCKMEM_STATUS_OK;
+}
+
+bool orion_sockmem_charge(size_t bytes)
+{
+    bool charged = false;
+    bool engaged = false;
+
+    spinlock_acquire(&sockmem_lock);
+    if (bytes <= sockmem.max - sockmem.stats.allocated) {
+        sockmem.stats.allocated += bytes;
+        if (sockmem.stats.allocated > sockmem.stats.peak) {
+            sockmem.stats.peak = sockmem.stats.allocated;
+        }
+        if (!sockmem.throttled && sockmem.stats.allocated > sockmem.pressure) {
+            sockmem.throttled = true;
+            sockmem.stats.pressure_events++;
+            engaged = true;
+        }
+        charged = true;
+    } else {
+        sockmem.stats.refused++;
+    }
+    spinlock_release(&sockmem_lock);
+
+    if (engaged) {
+        klog_warning(KLOG_CAT_KERNEL, "Socket memory under pressure: %zu bytes, pausing receive pools",
+                     sockmem.stats.allocated);
+    }
+    return charged;
+}
+
+void orion_sockmem_uncharge(size_t bytes)
+{
+    bool lifted = false;
+
+    spinlock_acquire(&sockmem_lock);
+    sockmem.stats.allocated -= bytes < sockmem.stats.allocated ? bytes : sockmem.stats.allocated;
+    if (sockmem.throttled && sockmem.stats.allocated < sockmem.min) {
+        sockmem.throttled = false;
+        lifted = true;
+    }
+    spinlock_release(&sockmem_lock);
+
+    if (lifted) {
+        klog_info(KLOG_CAT_KERNEL, "Socket memory pressure relieved, resuming receive pools");
+        orion_rx_pool_resume();
+    }
+}
+
+orion_sockmem_level_t orion_sockmem_level(void)
+{
+    orion_sockmem_level_t level;
+
+    spinlock_acquire(&sockmem_lock);
+    if (sockmem.throttled) {
+        level = ORION_SOCKMEM_PRESSURE;
+    } else if (sockmem.stats.allocated >= sockmem.min) {
+        level = ORION_SOCKMEM_MODERATE;
+    } else {
+        level = ORION_SOCKMEM_NORMAL;
+    }
+    spinlock_release(&sockmem_lock);
+    return level;
+}
+
+bool orion_sockmem_may_grow(void)
+{
+    return orion_sockmem_level() != ORION_SOCKMEM_PRESSURE;
+}
+
+void orion_sockmem_get_stats(orion_sockmem_stats_t *stats)
+{
+    if (!stats) {
+        return;
+    }
+
+    spinlock_acquire(&sockmem_lock);
+    *stats = sockmem.stats;
+    spinlock_release(&sockmem_lock);
+}
@@ -0,0 +1,91 @@
+/*
+ * Orion Operating System - Socket Buffer Memory
+ *
+ * Accounting of the memory held by socket send and receive buffers across
+ * the network server, with three limits in the spirit of tcp_mem:
+ *
+ *   below min       buffers grow freely
+ *   above pressure  buffers stop growing and receive pools are paused
+ *                   until usage falls back below min
+ *   max             charges past it are refused
+ *
+ * Pausing a receive pool leaves frames on its rx ring and keeps their
+ * buffers off the fill ring, so the driver runs out of buffers to post
+ * and the device stops delivering: a slow consumer costs a bounded
+ * amount of memory instead of an ever growing queue.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_SOCKET_MEMORY_H
+#define ORION_SOCKET_MEMORY_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_SOCKMEM_DEFAULT_MIN (4U << 20)
+#define ORION_SOCKMEM_DEFAULT_PRESSURE (12U << 20)
+#define ORION_SOCKMEM_DEFAULT_MAX (16U << 20)
+
+    typedef enum
+    {
+        ORION_SOCKMEM_NORMAL = 0, // Below min
+        ORION_SOCKMEM_MODERATE,   // Between min and pressure, no throttle in effect
+        ORION_SOCKMEM_PRESSURE    // Throttled until usage falls below min
+    } orion_sockmem_level_t;
+
+    typedef struct
+    {
+        size_t allocated;         // Bytes currently charged
+        size_t peak;              // Highest charge seen
+        uint64_t refused;         // Charges refused at max
+        uint64_t pressure_events; // Times the pressure limit was crossed
+    } orion_sockmem_stats_t;
+
+    /**
+     * @brief Set the limits (min <= pressure <= max)
+     * @return 0 or a negative errno
+     */
+    int orion_sockmem_set_limits(size_t min, size_t pressure, size_t max);
+
+    /**
+     * @brief Charge bytes of socket buffer
+     * @param bytes Bytes about to be allocated
+     * @return true if the charge fits under max
+     */
+    bool orion_sockmem_charge(size_t bytes);
+
+    /**
+     * @brief Return bytes of socket buffer; may resume paused receive pools
+     * @param bytes Bytes freed
+     */
+    void orion_sockmem_uncharge(size_t bytes);
+
+    /**
+     * @brief Current pressure level
+     */
+    orion_sockmem_level_t orion_sockmem_level(void);
+
+    /**
+     * @brief Whether buffers may grow past their initial size
+     */
+    bool orion_sockmem_may_grow(void);
+
+    /**
+     * @brief Copy out the accounting counters
+     */
+    void orion_sockmem_get_stats(orion_sockmem_stats_t *stats);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_SOCKET_MEMORY_H
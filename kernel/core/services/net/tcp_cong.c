@@ -0,0 +1,299 @@
+/*
+ * Orion Operating System - TCP Congestion Control Implementation
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "tcp_cong.h"
+#include "tcp_ip_stack.h"
+#include <orion/klog.h>
+#include <orion/spinlock.h>
+#include <string.h>
+
+static const orion_tcp_cong_ops_t *cong_algorithms[ORION_TCP_CC_MAX_ALGORITHMS];
+static spinlock_t cong_lock = SPINLOCK_INITIALIZER;
+
+/* ============================================================================
+ * Registry
+ * ============================================================================ */
+
+int orion_tcp_cong_register(const orion_tcp_cong_ops_t *ops)
+{
+    if (!ops || !ops->init || !ops->on_ack || !ops->on_loss || !ops->on_rto) {
+        return -1;
+    }
+
+    int result = -1;
+    spinlock_acquire(&cong_lock);
+    int free_slot = -1;
+    bool taken = false;
+    for (int i = 0; i < ORION_TCP_CC_MAX_ALGORITHMS; i++) {
+        if (cong_algorithms[i] && cong_algorithms[i]->id == ops->id) {
+            taken = true;
+        } else if (!cong_algorithms[i] && free_slot < 0) {
+            free_slot = i;
+        }
+    }
+    if (!taken && free_slot >= 0) {
+        cong_algorithms[free_slot] = ops;
+        result = 0;
+    }
+    spinlock_release(&cong_lock);
+
+    if (result == 0) {
+        klog_info(KLOG_CAT_KERNEL, "TCP congestion control %s registered", ops->name);
+    }
+    return result;
+}
+
+void orion_tcp_cong_unregister(uint32_t id)
+{
+    spinlock_acquire(&cong_lock);
+    for (int i = 0; i < ORION_TCP_CC_MAX_ALGORITHMS; i++) {
+        if (cong_algorithms[i] && cong_algorithms[i]->id == id) {
+            cong_algorithms[i] = NULL;
+        }
+    }
+    spinlock_release(&cong_lock);
+}
+
+const orion_tcp_cong_ops_t *orion_tcp_cong_find(uint32_t id)
+{
+    const orion_tcp_cong_ops_t *ops = NULL;
+    spinlock_acquire(&cong_lock);
+    for (int i = 0; i < ORION_TCP_CC_MAX_ALGORITHMS; i++) {
+        if (cong_algorithms[i] && cong_algorithms[i]->id == id) {
+            ops = cong_algorithms[i];
+            break;
+        }
+    }
+    spinlock_release(&cong_lock);
+    return ops;
+}
+
+void orion_tcp_cong_init(void)
+{
+    orion_tcp_cong_register(&orion_tcp_reno_ops);
+    orion_tcp_cong_register(&orion_tcp_cubic_ops);
+}
+
+/* ============================================================================
+ * Reno
+ * ============================================================================ */
+
+// Grow by one segment per segment delivered up to ssthresh; returns what is left
+static uint32_t cong_slow_start(orion_tcp_cc_t *cc, uint32_t acked)
+{
+    if (cc->cwnd >= cc->ssthresh) {
+        return acked;
+    }
+    uint32_t room = cc->ssthresh - cc->cwnd;
+    uint32_t grow = acked < room ? acked : room;
+    cc->cwnd += grow;
+    return acked - grow;
+}
+
+static uint32_t cong_halve(const orion_tcp_cc_t *cc)
+{
+    uint32_t half = cc->cwnd / 2;
+    return half < ORION_TCP_CC_MIN_SSTHRESH ? ORION_TCP_CC_MIN_SSTHRESH : half;
+}
+
+static void reno_init(orion_tcp_cc_t *cc)
+{
+    memset(cc, 0, sizeof(*cc));
+    cc->cwnd = ORION_TCP_CC_INIT_CWND;
+    cc->ssthresh = ORION_TCP_CC_INFINITE_SSTHRESH;
+}
+
+static void reno_on_ack(orion_tcp_cc_t *cc, uint32_t acked, uint64_t rtt_ns, uint64_t now_ns)
+{
+    (void)rtt_ns;
+    (void)now_ns;
+
+    acked = cong_slow_start(cc, acked);
+    // One segment per window of segments delivered
+    cc->cwnd_cnt += acked;
+    if (cc->cwnd_cnt >= cc->cwnd) {
+        cc->cwnd_cnt -= cc->cwnd;
+        cc->cwnd++;
+    }
+}
+
+static void reno_on_loss(orion_tcp_cc_t *cc, uint64_t now_ns)
+{
+    (void)now_ns;
+    cc->ssthresh = cong_halve(cc);
+    cc->cwnd = cc->ssthresh;
+    cc->cwnd_cnt = 0;
+}
+
+static void reno_on_rto(orion_tcp_cc_t *cc, uint64_t now_ns)
+{
+    (void)now_ns;
+    cc->ssthresh = cong_halve(cc);
+    cc->cwnd = 1;
+    cc->cwnd_cnt = 0;
+}
+
+const orion_tcp_cong_ops_t orion_tcp_reno_ops = {
+    .name = "reno",
+    .id = ORION_TCP_CC_RENO,
+    .init = reno_init,
+    .on_ack = reno_on_ack,
+    .on_loss = reno_on_loss,
+    .on_rto = reno_on_rto,
+};
+
+/* ============================================================================
+ * CUBIC
+ * ============================================================================ */
+
+#define CUBIC_FP_SHIFT 10
+#define CUBIC_BETA 717               // 0.7 << 10, multiplicative decrease
+#define CUBIC_ALPHA 542              // 3 * (1 - beta) / (1 + beta) << 10, Reno-friendly growth
+#define CUBIC_K_SCALE 2500000000ULL  // 1 / C in ms^3 per segment, C = 0.4
+#define CUBIC_MAX_DELTA_MS 100000    // |t - K| past which the curve is flat enough
+
+typedef struct
+{
+    uint64_t epoch_start_ns; // Start of the current growth epoch, 0 before its first ACK
+    uint64_t min_rtt_ns;
+    uint64_t k_ms;     // Time the curve takes back to origin
+    uint64_t w_est_fp; // Window standard TCP would have, segments << CUBIC_FP_SHIFT
+    uint32_t w_max;    // Window before the last reduction
+    uint32_t origin;   // Plateau of the curve
+} cubic_t;
+
+static cubic_t *cubic_state(orion_tcp_cc_t *cc)
+{
+    return (cubic_t *)cc->priv;
+}
+
+static uint64_t cube_root(uint64_t x)
+{
+    uint64_t lo = 0;
+    uint64_t hi = 1ULL << 21; // Its cube is the first past 2^63
+    while (lo < hi) {
+        uint64_t mid = (lo + hi + 1) / 2;
+        if (mid * mid * mid <= x) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    return lo;
+}
+
+static void cubic_init(orion_tcp_cc_t *cc)
+{
+    reno_init(cc);
+}
+
+static void cubic_on_ack(orion_tcp_cc_t *cc, uint32_t acked, uint64_t rtt_ns, uint64_t now_ns)
+{
+    cubic_t *ca = cubic_state(cc);
+    if (rtt_ns && (!ca->min_rtt_ns || rtt_ns < ca->min_rtt_ns)) {
+        ca->min_rtt_ns = rtt_ns;
+    }
+
+    acked = cong_slow_start(cc, acked);
+    if (!acked) {
+        return;
+    }
+
+    if (!ca->epoch_start_ns) {
+        ca->epoch_start_ns = now_ns;
+        cc->cwnd_cnt = 0;
+        ca->w_est_fp = (uint64_t)cc->cwnd << CUBIC_FP_SHIFT;
+        if (cc->cwnd < ca->w_max) {
+            // K = cbrt((W_max - cwnd) / C)
+            ca->k_ms = cube_root((uint64_t)(ca->w_max - cc->cwnd) * CUBIC_K_SCALE);
+            ca->origin = ca->w_max;
+        } else {
+            ca->k_ms = 0;
+            ca->origin = cc->cwnd;
+        }
+    }
+
+    // W_cubic(t + RTT) = C * (t + RTT - K)^3 + W_max
+    int64_t t_ms = (int64_t)((now_ns - ca->epoch_start_ns + ca->min_rtt_ns) / 1000000);
+    int64_t d = t_ms - (int64_t)ca->k_ms;
+    if (d > CUBIC_MAX_DELTA_MS) {
+        d = CUBIC_MAX_DELTA_MS;
+    } else if (d < -CUBIC_MAX_DELTA_MS) {
+        d = -CUBIC_MAX_DELTA_MS;
+    }
+    int64_t target = (int64_t)ca->origin + d * d * d / (int64_t)CUBIC_K_SCALE;
+    if (target < 1) {
+        target = 1;
+    }
+    // Never more than half a window of growth per RTT
+    int64_t limit = (int64_t)cc->cwnd + cc->cwnd / 2;
+    if (target > limit) {
+        target = limit;
+    }
+
+    // Grow at least as fast as standard TCP would on this path
+    ca->w_est_fp += (uint64_t)acked * CUBIC_ALPHA / cc->cwnd;
+    int64_t w_est = (int64_t)(ca->w_est_fp >> CUBIC_FP_SHIFT);
+    if (w_est > target) {
+        target = w_est;
+    }
+
+    // Segments to deliver per one segment of growth
+    uint32_t cnt = target > (int64_t)cc->cwnd ? (uint32_t)(cc->cwnd / (target - cc->cwnd)) : 100 * cc->cwnd;
+    if (cnt == 0) {
+        cnt = 1;
+    }
+    cc->cwnd_cnt += acked;
+    if (cc->cwnd_cnt >= cnt) {
+        cc->cwnd += cc->cwnd_cnt / cnt;
+        cc->cwnd_cnt %= cnt;
+    }
+}
+
+static void cubic_reduce(orion_tcp_cc_t *cc)
+{
+    cubic_t *ca = cubic_state(cc);
+    ca->epoch_start_ns = 0;
+
+    // Fast convergence: a flow losing below its previous plateau releases
+    // bandwidth for newcomers
+    if (cc->cwnd < ca->w_max) {
+        ca->w_max = (uint32_t)(((uint64_t)cc->cwnd * ((1 << CUBIC_FP_SHIFT) + CUBIC_BETA)) >> (CUBIC_FP_SHIFT + 1));
+    } else {
+        ca->w_max = cc->cwnd;
+    }
+
+    uint32_t ssthresh = (uint32_t)(((uint64_t)cc->cwnd * CUBIC_BETA) >> CUBIC_FP_SHIFT);
+    cc->ssthresh = ssthresh < ORION_TCP_CC_MIN_SSTHRESH ? ORION_TCP_CC_MIN_SSTHRESH : ssthresh;
+    cc->cwnd_cnt = 0;
+}
+
+static void cubic_on_loss(orion_tcp_cc_t *cc, uint64_t now_ns)
+{
+    (void)now_ns;
+    cubic_reduce(cc);
+    cc->cwnd = cc->ssthresh;
+}
+
+static void cubic_on_rto(orion_tcp_cc_t *cc, uint64_t now_ns)
+{
+    (void)now_ns;
+    cubic_reduce(cc);
+    cc->cwnd = 1;
+}
+
+const orion_tcp_cong_ops_t orion_tcp_cubic_ops = {
+    .name = "cubic",
+    .id = ORION_TCP_CC_CUBIC,
+    .init = cubic_init,
+    .on_ack = cubic_on_ack,
+    .on_loss = cubic_on_loss,
+    .on_rto = cubic_on_rto,
+};
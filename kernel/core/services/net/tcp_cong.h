@@ -0,0 +1,92 @@
+/*
+ * Orion Operating System - TCP Congestion Control
+ *
+ * Congestion control algorithms are plugged in through a table of
+ * callbacks registered under their orion_tcp_cc_algorithm_t value, and
+ * each connection picks its own. The stack calls the algorithm for the
+ * segments every ACK delivers, once when it enters loss recovery and on
+ * a retransmission timeout; the algorithm owns the congestion window and
+ * slow start threshold in between.
+ *
+ * Reno and CUBIC (RFC 9438) are built in. CUBIC grows the window as a
+ * cubic function of the time since the last reduction, so it regains its
+ * previous size quickly, probes carefully around it and takes long fat
+ * paths to full speed in seconds rather than minutes. Its computations are
+ * in integer milliseconds and fixed-point segments.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_TCP_CONG_H
+#define ORION_TCP_CONG_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_TCP_CC_MAX_ALGORITHMS 16
+#define ORION_TCP_CC_INIT_CWND 10   // Initial window in segments (RFC 6928)
+#define ORION_TCP_CC_MIN_SSTHRESH 2 // Smallest threshold after a reduction
+#define ORION_TCP_CC_INFINITE_SSTHRESH 0x7FFFFFFF
+
+    // Congestion state of a connection, windows in segments
+    typedef struct
+    {
+        uint32_t cwnd;     // Congestion window
+        uint32_t ssthresh; // Slow start threshold
+        uint32_t cwnd_cnt; // Segments delivered toward the next increase
+        uint64_t priv[8];  // Private to the algorithm
+    } orion_tcp_cc_t;
+
+    typedef struct orion_tcp_cong_ops
+    {
+        const char *name;
+        uint32_t id; // orion_tcp_cc_algorithm_t
+
+        // Start from the initial window
+        void (*init)(orion_tcp_cc_t *cc);
+        // Segments newly delivered outside recovery, with the RTT sample of the ACK (0 if none)
+        void (*on_ack)(orion_tcp_cc_t *cc, uint32_t acked, uint64_t rtt_ns, uint64_t now_ns);
+        // Loss detected, once per recovery episode
+        void (*on_loss)(orion_tcp_cc_t *cc, uint64_t now_ns);
+        // Retransmission timeout
+        void (*on_rto)(orion_tcp_cc_t *cc, uint64_t now_ns);
+    } orion_tcp_cong_ops_t;
+
+    extern const orion_tcp_cong_ops_t orion_tcp_reno_ops;
+    extern const orion_tcp_cong_ops_t orion_tcp_cubic_ops;
+
+    /**
+     * @brief Register an algorithm
+     * @param ops Callbacks, kept for the lifetime of the stack
+     * @return 0, or -1 if the id is taken or the table is full
+     */
+    int orion_tcp_cong_register(const orion_tcp_cong_ops_t *ops);
+
+    /**
+     * @brief Remove an algorithm; connections using it keep it until they close
+     */
+    void orion_tcp_cong_unregister(uint32_t id);
+
+    /**
+     * @brief Algorithm registered under an id, NULL if none
+     */
+    const orion_tcp_cong_ops_t *orion_tcp_cong_find(uint32_t id);
+
+    /**
+     * @brief Register the built-in algorithms
+     */
+    void orion_tcp_cong_init(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_TCP_CONG_H
@@ -12,11 +12,17 @@
  */
 
 #include "tcp_ip_stack.h"
+#include "socket_memory.h"
+#include "igmp.h"
+#include "netns.h"
+#include "ping.h"
+#include "route.h"
 #include <orion/klog.h>
 #include <orion/mm.h>
 #include <orion/string.h>
 #include <orion/spinlock.h>
 #include <orion/time.h>
+#include <orion/wallclock.h>
 #include <string.h>
 
 // Global TCP/IP stack state
@@ -33,15 +39,9 @@ static struct {
 static orion_tcp_connection_t *tcp_connections = NULL;
 static spinlock_t tcp_lock = SPINLOCK_INITIALIZER;
 
-// IP routing table
-static struct {
-    uint32_t dst_ip;
-    uint32_t dst_mask;
-    uint32_t gateway;
-    char interface[32];
-} ip_routes[256];
-static int route_count = 0;
-static spinlock_t route_lock = SPINLOCK_INITIALIZER;
+// Stack-wide counters; the paths bumping them hold different locks, if any
+static orion_tcpip_stats_t tcpip_stats;
+#define TCPIP_STAT_INC(field) __atomic_fetch_add(&tcpip_stats.field, 1, __ATOMIC_RELAXED)
 
 // NAT table
 static struct {
@@ -73,6 +73,27 @@ static spinlock_t firewall_lock = SPINLOCK_INITIALIZER;
  * TCP Functions
  * ============================================================================ */
 
+// Largest segment the peer takes once options are settled
+static uint32_t tcp_mss(const orion_tcp_connection_t *conn)
+{
+    return conn->options.mss ? conn->options.mss : ORION_TCP_MSS;
+}
+
+// Pick the connection's algorithm, CUBIC or Reno when it is not registered
+static void tcp_cong_attach(orion_tcp_connection_t *conn, uint32_t algorithm)
+{
+    const orion_tcp_cong_ops_t *ops = orion_tcp_cong_find(algorithm);
+    if (!ops) {
+        ops = orion_tcp_cong_find(ORION_TCP_CC_CUBIC);
+    }
+    if (!ops) {
+        ops = &orion_tcp_reno_ops;
+    }
+    conn->cong = ops;
+    conn->congestion_control = ops->id;
+    ops->init(&conn->cc);
+}
+
 int orion_tcp_init(const orion_tcp_config_t *config)
 {
     if (tcpip_stack.tcp_initialized) {
@@ -104,6 +125,8 @@ int orion_tcp_init(const orion_tcp_config_t *config)
         tcpip_stack.tcp_config.tcp_congestion_control = ORION_TCP_CC_CUBIC;
     }
 
+    orion_tcp_cong_init();
+
     tcpip_stack.tcp_initialized = true;
     tcp_connections = NULL;
 
@@ -163,28 +186,38 @@ orion_tcp_connection_t *orion_tcp_connect(uint32_t local_ip, uint16_t local_port
     conn->state = ORION_TCP_STATE_SYN_SENT;
     conn->seq_num = orion_get_timestamp() & 0xFFFFFFFF;
     conn->window_size = 65535;
-    conn->ssthresh = 65535;
-    conn->cwnd = 1;
-
-    // Allocate buffers
-    conn->send_buffer_size = 65536;
-    conn->recv_buffer_size = 65536;
+    conn->rto = ORION_TCP_RTO_INITIAL_NS;
+    tcp_cong_attach(conn, tcpip_stack.tcp_config.tcp_congestion_control);
+
+    // Allocate buffers, small ones while socket memory is under pressure
+    bool pressure = !orion_sockmem_may_grow();
+    conn->send_buffer_size = pressure ? ORION_TCP_WMEM_MIN : ORION_TCP_WMEM_DEFAULT;
+    conn->recv_buffer_size = pressure ? ORION_TCP_RMEM_MIN : ORION_TCP_RMEM_DEFAULT;
+    if (!orion_sockmem_charge(conn->send_buffer_size + conn->recv_buffer_size)) {
+        klog_error(KLOG_CAT_KERNEL, "Socket memory exhausted, refusing TCP connection");
+        kfree(conn);
+        return NULL;
+    }
     conn->send_buffer = kmalloc(conn->send_buffer_size);
     conn->recv_buffer = kmalloc(conn->recv_buffer_size);
 
-    if (!conn->send_buffer || !conn->recv_buffer) {
+    if (!conn->send_buffer || !conn->recv_buffer || orion_tcp_sb_init(&conn->scoreboard, 0) != 0) {
         klog_error(KLOG_CAT_KERNEL, "Failed to allocate TCP buffers");
         if (conn->send_buffer) kfree(conn->send_buffer);
         if (conn->recv_buffer) kfree(conn->recv_buffer);
+        orion_sockmem_uncharge(conn->send_buffer_size + conn->recv_buffer_size);
         kfree(conn);
         return NULL;
     }
+    conn->rcv_wnd = conn->recv_buffer_size;
+    conn->rcv_space_time = wallclock_monotonic_ns();
 
     // Add to connection list
     spinlock_acquire(&tcp_lock);
     conn->next = tcp_connections;
     tcp_connections = conn;
     spinlock_release(&tcp_lock);
+    TCPIP_STAT_INC(tcp_active_opens);
 
     klog_info(KLOG_CAT_KERNEL, "TCP connection created: %u:%u -> %u:%u",
               local_ip, local_port, remote_ip, remote_port);
@@ -203,7 +236,9 @@ orion_tcp_connection_t *orion_tcp_accept(orion_tcp_connection_t *listener)
         return NULL;
     }
 
-    // In a real implementation, this would wait for incoming connections
+    // In a real implementation, this would wait for incoming connections.
+    // Accepted connections inherit listener->tls_listener (taking a
+    // reference) so that TLS is terminated on their first send/recv.
     // For now, return NULL to indicate no pending connections
     return NULL;
 }
@@ -227,6 +262,7 @@ orion_tcp_connection_t *orion_tcp_listen(uint32_t local_ip, uint16_t local_port,
     conn->local_port = local_port;
     conn->state = ORION_TCP_STATE_LISTEN;
     conn->window_size = 65535;
+    tcp_cong_attach(conn, tcpip_stack.tcp_config.tcp_congestion_control);
 
     // Add to connection list
     spinlock_acquire(&tcp_lock);
@@ -240,6 +276,211 @@ orion_tcp_connection_t *orion_tcp_listen(uint32_t local_ip, uint16_t local_port,
     return conn;
 }
 
+int orion_tcp_listen_tls(orion_tcp_connection_t *listener, uint64_t certificate_handle, uint64_t key_handle)
+{
+    if (!tcpip_stack.tcp_initialized || !listener) {
+        return -1;
+    }
+
+    if (listener->state != ORION_TCP_STATE_LISTEN || listener->tls_listener) {
+        klog_error(KLOG_CAT_KERNEL, "TLS can only be enabled once on a listening connection");
+        return -1;
+    }
+
+    listener->tls_listener = orion_tls_listener_create(certificate_handle, key_handle);
+    if (!listener->tls_listener) {
+        return -1;
+    }
+
+    klog_info(KLOG_CAT_KERNEL, "TLS termination enabled on %u:%u",
+              listener->local_ip, listener->local_port);
+    return 0;
+}
+
+int orion_tcp_start_tls(orion_tcp_connection_t *conn, uint64_t certificate_handle, uint64_t key_handle)
+{
+    if (!tcpip_stack.tcp_initialized || !conn) {
+        return -1;
+    }
+
+    if (conn->state != ORION_TCP_STATE_ESTABLISHED || conn->tls_listener || conn->tls_session) {
+        klog_error(KLOG_CAT_KERNEL, "TLS can only be started once on an established connection");
+        return -1;
+    }
+
+    // The session attaches on the next send/recv like on accepted connections
+    conn->tls_listener = orion_tls_listener_create(certificate_handle, key_handle);
+    if (!conn->tls_listener) {
+        return -1;
+    }
+
+    klog_info(KLOG_CAT_KERNEL, "TLS started on %u:%u -> %u:%u",
+              conn->local_ip, conn->local_port, conn->remote_ip, conn->remote_port);
+    return 0;
+}
+
+int orion_tcp_start_tls_client(orion_tcp_connection_t *conn, uint64_t anchor_handle, const char *server_name)
+{
+    if (!tcpip_stack.tcp_initialized || !conn) {
+        return -1;
+    }
+
+    bool opening = conn->state == ORION_TCP_STATE_SYN_SENT || conn->state == ORION_TCP_STATE_ESTABLISHED;
+    if (!opening || conn->tls_listener || conn->tls_session || conn->bytes_received > 0) {
+        klog_error(KLOG_CAT_KERNEL, "TLS client can only start on a fresh outgoing connection");
+        return -1;
+    }
+
+    // The ClientHello waits in the session until the first send/recv
+    conn->tls_session = orion_tls_client_create(anchor_handle, server_name);
+    if (!conn->tls_session) {
+        return -1;
+    }
+
+    klog_info(KLOG_CAT_KERNEL, "TLS client started on %u:%u -> %u:%u (%s)",
+              conn->local_ip, conn->local_port, conn->remote_ip, conn->remote_port, server_name);
+    return 0;
+}
+
+static size_t tcp_queue_raw(orion_tcp_connection_t *conn, const void *data, size_t len)
+{
+    size_t space = conn->send_buffer_size - conn->send_buffer_used;
+    size_t copy_len = (len < space) ? len : space;
+
+    memcpy((char*)conn->send_buffer + conn->send_buffer_used, data, copy_len);
+    conn->send_buffer_used += copy_len;
+    return copy_len;
+}
+
+static size_t tcp_dequeue_raw(orion_tcp_connection_t *conn, void *data, size_t len)
+{
+    size_t copy_len = (len < conn->recv_buffer_used) ? len : conn->recv_buffer_used;
+    if (data) {
+        memcpy(data, conn->recv_buffer, copy_len);
+    }
+
+    // Move remaining data to beginning of buffer
+    if (copy_len < conn->recv_buffer_used) {
+        memmove(conn->recv_buffer,
+                (char*)conn->recv_buffer + copy_len,
+                conn->recv_buffer_used - copy_len);
+    }
+    conn->recv_buffer_used -= copy_len;
+    return copy_len;
+}
+
+/* ============================================================================
+ * Buffer Autotuning
+ * ============================================================================ */
+
+// Replace a connection buffer by a larger one, keeping the bytes in use
+static int tcp_grow_buffer(void **buffer, size_t *size, size_t used, size_t new_size)
+{
+    if (new_size <= *size) {
+        return 0;
+    }
+    if (!orion_sockmem_charge(new_size - *size)) {
+        return -1;
+    }
+    void *grown = kmalloc(new_size);
+    if (!grown) {
+        orion_sockmem_uncharge(new_size - *size);
+        return -1;
+    }
+    memcpy(grown, *buffer, used);
+    kfree(*buffer);
+    *buffer = grown;
+    *size = new_size;
+    return 0;
+}
+
+// Advertise what the receive buffer can still take
+static void tcp_update_rcv_wnd(orion_tcp_connection_t *conn)
+{
+    conn->rcv_wnd = (uint32_t)(conn->recv_buffer_size - conn->recv_buffer_used);
+}
+
+// Dynamic right-sizing: what the reader drains in one round trip is the
+// bandwidth-delay product the receive window has to cover. The buffer is
+// sized to twice the best measurement so the sender's window can keep
+// growing; a reader slower than the link never raises the measurement.
+static void tcp_rcv_space_adjust(orion_tcp_connection_t *conn, size_t copied)
+{
+    uint64_t now = wallclock_monotonic_ns();
+    uint64_t rtt = conn->rtt ? conn->rtt : ORION_TCP_AUTOTUNE_DEFAULT_RTT_NS;
+    if (rtt < ORION_TCP_AUTOTUNE_MIN_RTT_NS) {
+        rtt = ORION_TCP_AUTOTUNE_MIN_RTT_NS;
+    }
+
+    conn->rcv_copied += copied;
+    if (now - conn->rcv_space_time < rtt) {
+        return;
+    }
+
+    if (conn->rcv_copied > conn->rcv_space) {
+        conn->rcv_space = conn->rcv_copied;
+        size_t target = 2 * conn->rcv_space;
+        if (target > ORION_TCP_RMEM_MAX) {
+            target = ORION_TCP_RMEM_MAX;
+        }
+        if (target > conn->recv_buffer_size && orion_sockmem_may_grow() &&
+            tcp_grow_buffer(&conn->recv_buffer, &conn->recv_buffer_size, conn->recv_buffer_used, target) == 0) {
+            klog_debug(KLOG_CAT_KERNEL, "TCP receive buffer grown to %zu bytes", target);
+        }
+    }
+    conn->rcv_copied = 0;
+    conn->rcv_space_time = now;
+}
+
+// The send buffer holds a congestion window in flight and as much again
+// queued behind it; anything more would only queue data the path cannot
+// take yet, so writers past that see a full buffer
+static void tcp_snd_buffer_adjust(orion_tcp_connection_t *conn)
+{
+    size_t target = 2 * (size_t)conn->cc.cwnd * tcp_mss(conn);
+    if (target > ORION_TCP_WMEM_MAX) {
+        target = ORION_TCP_WMEM_MAX;
+    }
+    if (target > conn->send_buffer_size && orion_sockmem_may_grow()) {
+        tcp_grow_buffer(&conn->send_buffer, &conn->send_buffer_size, conn->send_buffer_used, target);
+    }
+}
+
+// Move records produced by the TLS engine into the send buffer
+static void tcp_tls_flush(orion_tcp_connection_t *conn)
+{
+    size_t space = conn->send_buffer_size - conn->send_buffer_used;
+    conn->send_buffer_used += orion_tls_session_output(conn->tls_session,
+                                                       (char*)conn->send_buffer + conn->send_buffer_used,
+                                                       space);
+}
+
+static int tcp_tls_attach(orion_tcp_connection_t *conn)
+{
+    if (!conn->tls_listener || conn->tls_session) {
+        return 0;
+    }
+
+    conn->tls_session = orion_tls_session_create(conn->tls_listener);
+    return conn->tls_session ? 0 : -1;
+}
+
+// Feed received ciphertext to the TLS engine and answer handshake messages
+static int tcp_tls_pump(orion_tcp_connection_t *conn)
+{
+    ssize_t consumed = 0;
+
+    if (conn->recv_buffer_used > 0) {
+        consumed = orion_tls_session_input(conn->tls_session, conn->recv_buffer, conn->recv_buffer_used);
+        if (consumed > 0) {
+            tcp_dequeue_raw(conn, NULL, consumed);
+        }
+    }
+    // Also sends the ClientHello of client sessions
+    tcp_tls_flush(conn);
+    return consumed < 0 ? -1 : 0;
+}
+
 ssize_t orion_tcp_send(orion_tcp_connection_t *conn, const void *data, size_t len)
 {
     if (!tcpip_stack.tcp_initialized || !conn || !data) {
@@ -251,14 +492,40 @@ ssize_t orion_tcp_send(orion_tcp_connection_t *conn, const void *data, size_t le
         return -1;
     }
 
+    if (tcp_tls_attach(conn) != 0) {
+        return -1;
+    }
+
+    if (conn->tls_session) {
+        if (tcp_tls_pump(conn) != 0) {
+            return -1;
+        }
+        if (orion_tls_session_handshaking(conn->tls_session)) {
+            return 0;
+        }
+        // Records are sealed into the engine's output queue; a short count
+        // means the queue or the send buffer is full
+        ssize_t accepted = orion_tls_session_write(conn->tls_session, data, len);
+        tcp_tls_flush(conn);
+        if (accepted < 0) {
+            return -1;
+        }
+        conn->bytes_sent += accepted;
+        conn->packets_sent++;
+        klog_debug(KLOG_CAT_KERNEL, "TCP send (TLS): %zd bytes", accepted);
+        return accepted;
+    }
+
+    if (len > conn->send_buffer_size - conn->send_buffer_used) {
+        tcp_snd_buffer_adjust(conn);
+    }
     if (len > conn->send_buffer_size - conn->send_buffer_used) {
         klog_error(KLOG_CAT_KERNEL, "Send buffer full");
         return -1;
     }
 
     // Copy data to send buffer
-    memcpy((char*)conn->send_buffer + conn->send_buffer_used, data, len);
-    conn->send_buffer_used += len;
+    tcp_queue_raw(conn, data, len);
     conn->bytes_sent += len;
     conn->packets_sent++;
 
@@ -277,21 +544,33 @@ ssize_t orion_tcp_recv(orion_tcp_connection_t *conn, void *data, size_t len)
         return -1;
     }
 
+    if (tcp_tls_attach(conn) != 0) {
+        return -1;
+    }
+
+    if (conn->tls_session) {
+        tcp_tls_pump(conn);
+        // Decrypted data is still delivered after a failure or close_notify
+        // until the session is drained
+        ssize_t received = orion_tls_session_read(conn->tls_session, data, len);
+        tcp_update_rcv_wnd(conn);
+        if (received > 0) {
+            tcp_rcv_space_adjust(conn, received);
+            conn->bytes_received += received;
+            conn->packets_received++;
+            klog_debug(KLOG_CAT_KERNEL, "TCP receive (TLS): %zd bytes", received);
+        }
+        return received;
+    }
+
     if (conn->recv_buffer_used == 0) {
         return 0; // No data available
     }
 
     // Copy data from receive buffer
-    size_t copy_len = (len < conn->recv_buffer_used) ? len : conn->recv_buffer_used;
-    memcpy(data, conn->recv_buffer, copy_len);
-
-    // Move remaining data to beginning of buffer
-    if (copy_len < conn->recv_buffer_used) {
-        memmove(conn->recv_buffer, 
-                (char*)conn->recv_buffer + copy_len,
-                conn->recv_buffer_used - copy_len);
-    }
-    conn->recv_buffer_used -= copy_len;
+    size_t copy_len = tcp_dequeue_raw(conn, data, len);
+    tcp_rcv_space_adjust(conn, copy_len);
+    tcp_update_rcv_wnd(conn);
     conn->bytes_received += copy_len;
     conn->packets_received++;
 
@@ -299,6 +578,25 @@ ssize_t orion_tcp_recv(orion_tcp_connection_t *conn, void *data, size_t len)
     return copy_len;
 }
 
+int orion_tcp_tls_export(orion_tcp_connection_t *conn, const char *label, uint8_t *out, size_t out_len)
+{
+    if (!tcpip_stack.tcp_initialized || !conn || !label || !out) {
+        return -1;
+    }
+
+    if (conn->state != ORION_TCP_STATE_ESTABLISHED || tcp_tls_attach(conn) != 0 || !conn->tls_session) {
+        return -1;
+    }
+
+    if (tcp_tls_pump(conn) != 0) {
+        return -1;
+    }
+    if (orion_tls_session_handshaking(conn->tls_session)) {
+        return 1;
+    }
+    return orion_tls_session_export(conn->tls_session, label, out, out_len);
+}
+
 int orion_tcp_close(orion_tcp_connection_t *conn)
 {
     if (!tcpip_stack.tcp_initialized || !conn) {
@@ -308,6 +606,18 @@ int orion_tcp_close(orion_tcp_connection_t *conn)
     klog_info(KLOG_CAT_KERNEL, "Closing TCP connection: %u:%u -> %u:%u",
               conn->local_ip, conn->local_port, conn->remote_ip, conn->remote_port);
 
+    // Send close_notify before the FIN and drop the listener reference
+    if (conn->tls_session) {
+        orion_tls_session_close(conn->tls_session);
+        tcp_tls_flush(conn);
+        orion_tls_session_destroy(conn->tls_session);
+        conn->tls_session = NULL;
+    }
+    if (conn->tls_listener) {
+        orion_tls_listener_put(conn->tls_listener);
+        conn->tls_listener = NULL;
+    }
+
     // Remove from connection list
     spinlock_acquire(&tcp_lock);
     if (tcp_connections == conn) {
@@ -326,6 +636,10 @@ int orion_tcp_close(orion_tcp_connection_t *conn)
     // Free buffers
     if (conn->send_buffer) kfree(conn->send_buffer);
     if (conn->recv_buffer) kfree(conn->recv_buffer);
+    if (conn->send_buffer || conn->recv_buffer) {
+        orion_sockmem_uncharge(conn->send_buffer_size + conn->recv_buffer_size);
+    }
+    orion_tcp_sb_destroy(&conn->scoreboard);
 
     // Free connection
     kfree(conn);
@@ -348,165 +662,473 @@ int orion_tcp_set_congestion_control(orion_tcp_connection_t *conn,
         return -1;
     }
 
-    conn->congestion_control = algorithm;
-    klog_info(KLOG_CAT_KERNEL, "TCP congestion control set to algorithm %d", algorithm);
+    const orion_tcp_cong_ops_t *ops = orion_tcp_cong_find(algorithm);
+    if (!ops) {
+        klog_error(KLOG_CAT_KERNEL, "TCP congestion control algorithm %d not available", algorithm);
+        return -1;
+    }
+
+    // A connection already sending keeps its windows; the new algorithm
+    // only starts with fresh state of its own
+    orion_tcp_cc_t prior = conn->cc;
+    conn->cong = ops;
+    conn->congestion_control = ops->id;
+    ops->init(&conn->cc);
+    if (conn->state == ORION_TCP_STATE_ESTABLISHED) {
+        conn->cc.cwnd = prior.cwnd;
+        conn->cc.ssthresh = prior.ssthresh;
+    }
+    klog_info(KLOG_CAT_KERNEL, "TCP congestion control set to %s", ops->name);
     return 0;
 }
 
 /* ============================================================================
- * IP Functions
+ * TCP Options and Loss Recovery
  * ============================================================================ */
 
-int orion_ip_init(void)
+static void tcp_fill_offer(orion_tcp_connection_t *conn)
 {
-    if (tcpip_stack.ip_initialized) {
-        klog_error(KLOG_CAT_KERNEL, "IP stack already initialized");
-        return -1;
-    }
-
-    tcpip_stack.ip_initialized = true;
-    route_count = 0;
-
-    klog_info(KLOG_CAT_KERNEL, "IP stack initialized successfully");
-    return 0;
+    const orion_tcp_config_t *config = &tcpip_stack.tcp_config;
+    conn->syn_offer.mss = ORION_TCP_MSS;
+    conn->syn_offer.wscale = config->tcp_window_scaling != 0;
+    // Shift for the largest window autotuning may open
+    conn->syn_offer.wscale_shift = orion_tcp_wscale_for(ORION_TCP_RMEM_MAX);
+    conn->syn_offer.sack_permitted = config->tcp_sack != 0;
+    conn->syn_offer.timestamps = config->tcp_timestamps != 0;
 }
 
-int orion_ip_shutdown(void)
+size_t orion_tcp_syn_options(orion_tcp_connection_t *conn, uint8_t *buf, uint32_t ts_ecr)
 {
-    if (!tcpip_stack.ip_initialized) {
+    if (!conn || !buf) {
         return 0;
     }
-
-    klog_info(KLOG_CAT_KERNEL, "Shutting down IP stack");
-    tcpip_stack.ip_initialized = false;
-    return 0;
+    tcp_fill_offer(conn);
+    return orion_tcp_options_write_syn(buf, &conn->syn_offer, orion_tcp_ts_now(wallclock_monotonic_ns()), ts_ecr);
 }
 
-int orion_ip_send(uint32_t src_ip, uint32_t dst_ip, uint8_t protocol,
-                  const void *data, size_t len)
+size_t orion_tcp_segment_options(orion_tcp_connection_t *conn, uint8_t *buf)
 {
-    if (!tcpip_stack.ip_initialized || !data) {
-        return -1;
+    if (!conn || !buf) {
+        return 0;
     }
+    conn->options.last_ack_sent = conn->rcv_nxt;
+    return orion_tcp_options_write(buf, &conn->options, orion_tcp_ts_now(wallclock_monotonic_ns()),
+                                   conn->rcv_sack.blocks, conn->rcv_sack.count);
+}
 
-    // Create IP header
-    orion_ipv4_header_t *ip_header = (orion_ipv4_header_t*)data;
-    ip_header->version_ihl = 0x45; // IPv4, 5 words header
-    ip_header->tos = 0;
-    ip_header->total_length = htons(sizeof(orion_ipv4_header_t) + len);
-    ip_header->identification = htons(0x1234);
-    ip_header->flags_offset = 0;
-    ip_header->ttl = 64;
-    ip_header->protocol = protocol;
-    ip_header->checksum = 0;
-    ip_header->src_addr = htonl(src_ip);
-    ip_header->dst_addr = htonl(dst_ip);
-
-    // Calculate checksum
-    ip_header->checksum = orion_ip_checksum(ip_header, sizeof(orion_ipv4_header_t));
+uint16_t orion_tcp_advertised_window(const orion_tcp_connection_t *conn)
+{
+    uint32_t window = conn->rcv_wnd >> conn->options.rcv_wscale;
+    return window > 0xFFFF ? 0xFFFF : (uint16_t)window;
+}
 
-    klog_debug(KLOG_CAT_KERNEL, "IP packet sent: %u -> %u, protocol: %d, length: %zu",
-               src_ip, dst_ip, protocol, len);
+static void tcp_rtt_sample(orion_tcp_connection_t *conn, uint64_t rtt)
+{
+    if (!conn->rtt) {
+        conn->rtt = rtt;
+        conn->rttvar = rtt / 2;
+    } else {
+        uint64_t err = rtt > conn->rtt ? rtt - conn->rtt : conn->rtt - rtt;
+        conn->rttvar = (3 * conn->rttvar + err) / 4;
+        conn->rtt = (7 * conn->rtt + rtt) / 8;
+    }
 
-    return 0;
+    uint64_t rto = conn->rtt + 4 * conn->rttvar;
+    if (rto < ORION_TCP_RTO_MIN_NS) {
+        rto = ORION_TCP_RTO_MIN_NS;
+    } else if (rto > ORION_TCP_RTO_MAX_NS) {
+        rto = ORION_TCP_RTO_MAX_NS;
+    }
+    conn->rto = rto;
 }
 
-ssize_t orion_ip_recv(void *packet, size_t len)
+// The window is reduced once per episode, however many losses it holds
+static void tcp_enter_recovery(orion_tcp_connection_t *conn, uint64_t now)
 {
-    if (!tcpip_stack.ip_initialized || !packet || len < sizeof(orion_ipv4_header_t)) {
-        return -1;
+    if (conn->in_recovery) {
+        return;
     }
+    conn->in_recovery = true;
+    conn->recovery_point = conn->snd_nxt;
+    conn->cong->on_loss(&conn->cc, now);
+}
 
-    orion_ipv4_header_t *ip_header = (orion_ipv4_header_t*)packet;
-    
-    // Verify checksum
-    uint16_t original_checksum = ip_header->checksum;
-    ip_header->checksum = 0;
-    if (orion_ip_checksum(ip_header, sizeof(orion_ipv4_header_t)) != original_checksum) {
-        klog_error(KLOG_CAT_KERNEL, "IP packet checksum verification failed");
-        return -1;
+static void tcp_process_ack(orion_tcp_connection_t *conn, uint32_t ack, uint16_t window, bool syn,
+                            const orion_tcp_parsed_options_t *opts, uint64_t now)
+{
+    // The window of a SYN is never scaled
+    conn->snd_wnd = syn ? window : (uint32_t)window << conn->options.snd_wscale;
+
+    // An ACK for data never sent is ignored
+    if (orion_tcp_seq_after(ack, conn->snd_nxt)) {
+        return;
+    }
+    bool advanced = orion_tcp_seq_after(ack, conn->snd_una);
+
+    orion_tcp_ack_result_t result;
+    uint8_t blocks = conn->options.sack_ok ? opts->sack_count : 0;
+    orion_tcp_sb_on_ack(&conn->scoreboard, ack, opts->sack, blocks, now, &result);
+    if (advanced) {
+        conn->snd_una = ack;
+        conn->last_ack_time = now;
     }
 
-    uint32_t src_ip = ntohl(ip_header->src_addr);
-    uint32_t dst_ip = ntohl(ip_header->dst_addr);
-    uint8_t protocol = ip_header->protocol;
+    // RACK samples the most recently sent segment delivered; the echoed
+    // timestamp covers ACKs of segments the scoreboard no longer holds
+    uint64_t rtt = result.rtt_ns;
+    if (!rtt && advanced && conn->options.ts_ok && opts->has_timestamp && opts->ts_ecr) {
+        uint32_t ticks = orion_tcp_ts_now(now) - opts->ts_ecr;
+        rtt = (uint64_t)ticks * (1000000000ULL / ORION_TCP_TS_HZ);
+    }
+    if (rtt) {
+        tcp_rtt_sample(conn, rtt);
+    }
 
-    klog_debug(KLOG_CAT_KERNEL, "IP packet received: %u -> %u, protocol: %d, length: %zu",
-               src_ip, dst_ip, protocol, len);
+    // Recovery ends once everything outstanding when it started is acknowledged
+    if (conn->in_recovery && !orion_tcp_seq_before(conn->snd_una, conn->recovery_point)) {
+        conn->in_recovery = false;
+    }
+    if (result.newly_lost || result.tlp_loss) {
+        tcp_enter_recovery(conn, now);
+    } else if (!conn->in_recovery && result.delivered) {
+        conn->cong->on_ack(&conn->cc, result.delivered, rtt, now);
+    }
 
-    return len;
+    if (conn->scoreboard.count == 0) {
+        conn->rto_deadline = 0;
+    } else if (advanced) {
+        conn->rto_deadline = now + conn->rto;
+    }
+    orion_tcp_tlp_arm(&conn->scoreboard, conn->rtt, conn->rto, now);
 }
 
-int orion_ip_add_route(uint32_t dst_ip, uint32_t dst_mask, uint32_t gateway, const char *interface)
+int orion_tcp_input(orion_tcp_connection_t *conn, const orion_tcp_header_t *header, size_t header_len,
+                    size_t payload_len)
 {
-    if (!tcpip_stack.ip_initialized) {
+    if (!tcpip_stack.tcp_initialized || !conn || !header || header_len < sizeof(orion_tcp_header_t) ||
+        header_len > sizeof(orion_tcp_header_t) + ORION_TCP_OPTIONS_MAX) {
         return -1;
     }
+    TCPIP_STAT_INC(tcp_in_segs);
 
-    spinlock_acquire(&route_lock);
+    uint64_t now = wallclock_monotonic_ns();
+    bool syn = header->flags & ORION_TCP_FLAG_SYN;
+    orion_tcp_parsed_options_t opts;
+    if (orion_tcp_options_parse((const uint8_t *)(header + 1), header_len - sizeof(orion_tcp_header_t), syn,
+                                &opts) != 0) {
+        return -1;
+    }
 
-    if (route_count >= 256) {
-        klog_error(KLOG_CAT_KERNEL, "Routing table full");
-        spinlock_release(&route_lock);
+    uint32_t seq = ntohl(header->seq_num);
+    if (syn) {
+        // Passive opens answer with what the configuration offers
+        if (!conn->syn_offer.mss) {
+            tcp_fill_offer(conn);
+        }
+        orion_tcp_options_negotiate(&conn->options, &conn->syn_offer, &opts);
+        conn->options.last_ack_sent = seq + 1;
+    } else if (orion_tcp_paws_reject(&conn->options, &opts, header->flags & ORION_TCP_FLAG_RST)) {
+        klog_debug(KLOG_CAT_KERNEL, "TCP segment dropped by PAWS");
         return -1;
     }
+    orion_tcp_ts_update(&conn->options, &opts, seq);
 
-    // Check if route already exists
-    for (int i = 0; i < route_count; i++) {
-        if (ip_routes[i].dst_ip == dst_ip && ip_routes[i].dst_mask == dst_mask) {
-            // Update existing route
-            ip_routes[i].gateway = gateway;
-            strncpy(ip_routes[i].interface, interface, 31);
-            ip_routes[i].interface[31] = '\0';
-            
-            klog_info(KLOG_CAT_KERNEL, "IP route updated: %u/%u -> %u via %s",
-                      dst_ip, dst_mask, gateway, interface);
-            
-            spinlock_release(&route_lock);
-            return 0;
+    if (conn->options.sack_ok) {
+        if (payload_len && orion_tcp_seq_after(seq, conn->rcv_nxt)) {
+            orion_tcp_rcv_sack_add(&conn->rcv_sack, seq, seq + (uint32_t)payload_len);
         }
+        orion_tcp_rcv_sack_advance(&conn->rcv_sack, conn->rcv_nxt);
     }
 
-    // Add new route
-    ip_routes[route_count].dst_ip = dst_ip;
-    ip_routes[route_count].dst_mask = dst_mask;
-    ip_routes[route_count].gateway = gateway;
-    strncpy(ip_routes[route_count].interface, interface, 31);
-    ip_routes[route_count].interface[31] = '\0';
-    route_count++;
+    if (header->flags & ORION_TCP_FLAG_ACK) {
+        tcp_process_ack(conn, ntohl(header->ack_num), ntohs(header->window_size), syn, &opts, now);
+    }
+    return 0;
+}
 
-    klog_info(KLOG_CAT_KERNEL, "IP route added: %u/%u -> %u via %s",
-              dst_ip, dst_mask, gateway, interface);
+size_t orion_tcp_send_quota(const orion_tcp_connection_t *conn)
+{
+    uint32_t in_flight = orion_tcp_sb_in_flight(&conn->scoreboard);
+    if (in_flight >= conn->cc.cwnd) {
+        return 0;
+    }
+    size_t quota = (size_t)(conn->cc.cwnd - in_flight) * tcp_mss(conn);
 
-    spinlock_release(&route_lock);
-    return 0;
+    uint32_t outstanding = conn->snd_nxt - conn->snd_una;
+    size_t window = conn->snd_wnd > outstanding ? conn->snd_wnd - outstanding : 0;
+    return quota < window ? quota : window;
 }
 
-int orion_ip_remove_route(uint32_t dst_ip, uint32_t dst_mask)
+int orion_tcp_output(orion_tcp_connection_t *conn, uint32_t seq, uint32_t len)
 {
-    if (!tcpip_stack.ip_initialized) {
+    if (!conn) {
         return -1;
     }
 
-    spinlock_acquire(&route_lock);
+    uint64_t now = wallclock_monotonic_ns();
+    if (orion_tcp_sb_on_send(&conn->scoreboard, seq, len, now) != 0) {
+        return -1;
+    }
+    if (orion_tcp_seq_after(seq + len, conn->snd_nxt)) {
+        conn->snd_nxt = seq + len;
+    }
+    TCPIP_STAT_INC(tcp_out_segs);
+    conn->last_data_time = now;
+    if (!conn->rto_deadline) {
+        conn->rto_deadline = now + conn->rto;
+    }
+    orion_tcp_tlp_arm(&conn->scoreboard, conn->rtt, conn->rto, now);
+    return 0;
+}
 
-    for (int i = 0; i < route_count; i++) {
-        if (ip_routes[i].dst_ip == dst_ip && ip_routes[i].dst_mask == dst_mask) {
-            // Remove route by shifting remaining routes
-            for (int j = i; j < route_count - 1; j++) {
-                ip_routes[j] = ip_routes[j + 1];
-            }
-            route_count--;
+orion_tcp_txseg_t *orion_tcp_next_retransmit(orion_tcp_connection_t *conn)
+{
+    if (!conn || orion_tcp_sb_in_flight(&conn->scoreboard) >= conn->cc.cwnd) {
+        return NULL;
+    }
+    return orion_tcp_sb_next_lost(&conn->scoreboard);
+}
 
-            klog_info(KLOG_CAT_KERNEL, "IP route removed: %u/%u", dst_ip, dst_mask);
-            spinlock_release(&route_lock);
-            return 0;
+void orion_tcp_retransmitted(orion_tcp_connection_t *conn, orion_tcp_txseg_t *seg)
+{
+    uint64_t now = wallclock_monotonic_ns();
+    orion_tcp_sb_on_retransmit(&conn->scoreboard, seg, now);
+    conn->retransmissions++;
+    TCPIP_STAT_INC(tcp_retrans_segs);
+    if (!conn->rto_deadline) {
+        conn->rto_deadline = now + conn->rto;
+    }
+}
+
+orion_tcp_txseg_t *orion_tcp_timers(orion_tcp_connection_t *conn, bool has_new_data, bool *probe_new)
+{
+    *probe_new = false;
+    if (!conn) {
+        return NULL;
+    }
+
+    uint64_t now = wallclock_monotonic_ns();
+    orion_tcp_scoreboard_t *sb = &conn->scoreboard;
+
+    if (conn->rto_deadline && now >= conn->rto_deadline) {
+        // Everything not SACKed is retransmitted from a window of one,
+        // with the timer backed off until an ACK comes back
+        conn->timeouts++;
+        TCPIP_STAT_INC(tcp_timeouts);
+        orion_tcp_sb_on_rto(sb);
+        conn->cong->on_rto(&conn->cc, now);
+        conn->in_recovery = true;
+        conn->recovery_point = conn->snd_nxt;
+        conn->rto = conn->rto * 2 > ORION_TCP_RTO_MAX_NS ? ORION_TCP_RTO_MAX_NS : conn->rto * 2;
+        conn->rto_deadline = now + conn->rto;
+        return NULL;
+    }
+
+    if (sb->reo_deadline_ns && now >= sb->reo_deadline_ns && orion_tcp_sb_on_reo_timeout(sb, now)) {
+        tcp_enter_recovery(conn, now);
+    }
+
+    if (sb->tlp_deadline_ns && now >= sb->tlp_deadline_ns) {
+        orion_tcp_txseg_t *seg = orion_tcp_tlp_fire(sb, has_new_data, conn->snd_nxt);
+        *probe_new = sb->tlp_outstanding && !seg;
+        return seg;
+    }
+    return NULL;
+}
+
+/* ============================================================================
+ * IP Functions
+ * ============================================================================ */
+
+int orion_ip_init(void)
+{
+    if (tcpip_stack.ip_initialized) {
+        klog_error(KLOG_CAT_KERNEL, "IP stack already initialized");
+        return -1;
+    }
+
+    tcpip_stack.ip_initialized = true;
+    orion_route_init();
+
+    klog_info(KLOG_CAT_KERNEL, "IP stack initialized successfully");
+    return 0;
+}
+
+int orion_ip_shutdown(void)
+{
+    if (!tcpip_stack.ip_initialized) {
+        return 0;
+    }
+
+    klog_info(KLOG_CAT_KERNEL, "Shutting down IP stack");
+    tcpip_stack.ip_initialized = false;
+    return 0;
+}
+
+static int ip_send_packet(uint32_t src_ip, uint32_t dst_ip, uint8_t protocol, const void *data, size_t len,
+                          uint8_t ttl, bool dont_fragment)
+{
+    if (!tcpip_stack.ip_initialized || !data) {
+        return -1;
+    }
+
+    // Create IP header
+    orion_ipv4_header_t *ip_header = (orion_ipv4_header_t*)data;
+    ip_header->version_ihl = 0x45; // IPv4, 5 words header
+    ip_header->tos = 0;
+    ip_header->total_length = htons(sizeof(orion_ipv4_header_t) + len);
+    ip_header->identification = htons(0x1234);
+    ip_header->flags_offset = dont_fragment ? htons(ORION_IP_FLAG_DONT_FRAGMENT) : 0;
+    ip_header->ttl = ttl;
+    ip_header->protocol = protocol;
+    ip_header->checksum = 0;
+    ip_header->src_addr = htonl(src_ip);
+    ip_header->dst_addr = htonl(dst_ip);
+
+    // Calculate checksum
+    ip_header->checksum = orion_ip_checksum(ip_header, sizeof(orion_ipv4_header_t));
+    TCPIP_STAT_INC(ip_out_requests);
+
+    klog_debug(KLOG_CAT_KERNEL, "IP packet sent: %u -> %u, protocol: %d, length: %zu",
+               src_ip, dst_ip, protocol, len);
+
+    return 0;
+}
+
+static int ip_send_ttl(uint32_t src_ip, uint32_t dst_ip, uint8_t protocol, const void *data, size_t len,
+                       uint8_t ttl)
+{
+    return ip_send_packet(src_ip, dst_ip, protocol, data, len, ttl, false);
+}
+
+int orion_ip_send(uint32_t src_ip, uint32_t dst_ip, uint8_t protocol,
+                  const void *data, size_t len)
+{
+    return ip_send_ttl(src_ip, dst_ip, protocol, data, len, 64);
+}
+
+ssize_t orion_ip_recv(void *packet, size_t len)
+{
+    if (!tcpip_stack.ip_initialized || !packet || len < sizeof(orion_ipv4_header_t)) {
+        return -1;
+    }
+
+    orion_ipv4_header_t *ip_header = (orion_ipv4_header_t*)packet;
+    TCPIP_STAT_INC(ip_in_receives);
+    
+    // Verify checksum
+    uint16_t original_checksum = ip_header->checksum;
+    ip_header->checksum = 0;
+    if (orion_ip_checksum(ip_header, sizeof(orion_ipv4_header_t)) != original_checksum) {
+        klog_error(KLOG_CAT_KERNEL, "IP packet checksum verification failed");
+        TCPIP_STAT_INC(ip_in_hdr_errors);
+        return -1;
+    }
+
+    uint32_t src_ip = ntohl(ip_header->src_addr);
+    uint32_t dst_ip = ntohl(ip_header->dst_addr);
+    uint8_t protocol = ip_header->protocol;
+
+    klog_debug(KLOG_CAT_KERNEL, "IP packet received: %u -> %u, protocol: %d, length: %zu",
+               src_ip, dst_ip, protocol, len);
+
+    size_t header_len = (size_t)(ip_header->version_ihl & 0x0F) * 4;
+    if (header_len >= sizeof(orion_ipv4_header_t) && header_len < len) {
+        const uint8_t *payload = (const uint8_t *)packet + header_len;
+        if (protocol == ORION_IP_PROTOCOL_UDP) {
+            TCPIP_STAT_INC(ip_in_delivers);
+            orion_udp_input(src_ip, dst_ip, payload, len - header_len);
+        } else if (protocol == ORION_IP_PROTOCOL_IGMP) {
+            TCPIP_STAT_INC(ip_in_delivers);
+            orion_igmp_input(src_ip, dst_ip, payload, len - header_len);
+        } else if (protocol == ORION_IP_PROTOCOL_ICMP) {
+            TCPIP_STAT_INC(ip_in_delivers);
+            orion_icmp_input(src_ip, dst_ip, ip_header->ttl, payload, len - header_len);
         }
     }
 
-    spinlock_release(&route_lock);
-    klog_warning(KLOG_CAT_KERNEL, "IP route not found: %u/%u", dst_ip, dst_mask);
-    return -1;
+    return len;
+}
+
+// The flat routes of the stack live in the main table with the kernel origin
+static int ip_route_prefix(uint32_t dst_ip, uint32_t dst_mask, uint8_t *prefix, uint8_t *prefix_len)
+{
+    uint8_t len = 0;
+    while (len < 32 && (dst_mask & (0x80000000U >> len))) {
+        len++;
+    }
+    if (len < 32 && (dst_mask << len) != 0) {
+        return -1;
+    }
+    memset(prefix, 0, 16);
+    for (int i = 0; i < 4; i++) {
+        prefix[i] = (uint8_t)(dst_ip >> (24 - 8 * i));
+    }
+    *prefix_len = len;
+    return 0;
+}
+
+int orion_ip_add_route(uint32_t dst_ip, uint32_t dst_mask, uint32_t gateway, const char *interface)
+{
+    if (!tcpip_stack.ip_initialized || !interface) {
+        return -1;
+    }
+
+    orion_route_t route;
+    memset(&route, 0, sizeof(route));
+    if (ip_route_prefix(dst_ip, dst_mask, route.dst, &route.prefix_len) != 0) {
+        return -1;
+    }
+    route.table = ORION_ROUTE_TABLE_MAIN;
+    route.family = ORION_ROUTE_FAMILY_INET;
+    route.type = ORION_ROUTE_TYPE_UNICAST;
+    route.origin = ORION_ROUTE_ORIGIN_KERNEL;
+    route.nexthop_count = 1;
+    for (int i = 0; i < 4; i++) {
+        route.nexthops[0].gateway[i] = (uint8_t)(gateway >> (24 - 8 * i));
+    }
+    strncpy(route.nexthops[0].interface, interface, 31);
+    return orion_route_add(&route) == 0 ? 0 : -1;
+}
+
+int orion_ip_remove_route(uint32_t dst_ip, uint32_t dst_mask)
+{
+    if (!tcpip_stack.ip_initialized) {
+        return -1;
+    }
+
+    uint8_t prefix[16];
+    uint8_t prefix_len;
+    if (ip_route_prefix(dst_ip, dst_mask, prefix, &prefix_len) != 0 ||
+        orion_route_remove(ORION_ROUTE_TABLE_MAIN, ORION_ROUTE_FAMILY_INET, prefix, prefix_len,
+                           ORION_ROUTE_METRIC_ANY) != 0) {
+        klog_warning(KLOG_CAT_KERNEL, "IP route not found: %u/%u", dst_ip, dst_mask);
+        return -1;
+    }
+    return 0;
+}
+
+uint32_t orion_ip_route_mtu(uint32_t dst_ip)
+{
+    orion_route_flow_t flow;
+    memset(&flow, 0, sizeof(flow));
+    flow.family = ORION_ROUTE_FAMILY_INET;
+    for (int i = 0; i < 4; i++) {
+        flow.dst[i] = (uint8_t)(dst_ip >> (24 - 8 * i));
+    }
+    orion_route_result_t result;
+    if (orion_route_lookup(&flow, &result) != 0 || result.interface[0] == '\0') {
+        return 0;
+    }
+    orion_net_iface_config_t *iface = orion_net_get_interface(result.interface);
+    return iface ? iface->mtu : 0;
+}
+
+// Whether a packet of `len` bytes after the IP header would exceed the route MTU
+static bool ip_exceeds_mtu(uint32_t dst_ip, size_t len)
+{
+    uint32_t mtu = orion_ip_route_mtu(dst_ip);
+    return mtu != 0 && sizeof(orion_ipv4_header_t) + len > mtu;
 }
 
 /* ============================================================================
@@ -577,6 +1199,472 @@ ssize_t orion_udp_recv(uint32_t *src_ip, uint16_t *src_port,
     return payload_len;
 }
 
+/* ============================================================================
+ * UDP Endpoints
+ * ============================================================================ */
+
+typedef struct {
+    uint32_t src_ip;
+    uint16_t src_port;
+    uint16_t len;
+    uint8_t data[ORION_UDP_MAX_PAYLOAD];
+} udp_datagram_t;
+
+typedef struct {
+    uint32_t group; // 0 for a free slot
+    uint32_t ifaddr;
+} udp_membership_t;
+
+struct orion_udp_endpoint {
+    uint32_t local_ip;
+    uint16_t local_port;
+    uint32_t head;  // Oldest queued datagram
+    uint32_t count; // Queued datagrams
+    uint64_t dropped;
+    udp_membership_t groups[ORION_UDP_MAX_MEMBERSHIPS];
+    uint32_t multicast_if;  // Source address of multicast sent, 0 for local_ip
+    uint8_t multicast_ttl;
+    bool multicast_loop;    // Local members receive what is sent to their groups
+    uint8_t ttl;            // Time to live of unicast sent
+    bool dont_fragment;
+    uint32_t error_head;
+    uint32_t error_count;
+    orion_udp_error_t errors[ORION_UDP_ERROR_DEPTH]; // ICMP errors about datagrams sent
+    udp_datagram_t queue[ORION_UDP_QUEUE_DEPTH];
+};
+
+static orion_udp_endpoint_t *udp_endpoints[ORION_UDP_MAX_ENDPOINTS];
+static uint16_t udp_next_ephemeral = ORION_UDP_EPHEMERAL_FIRST;
+static spinlock_t udp_lock = SPINLOCK_INITIALIZER;
+
+// Whether endpoints bound to `a` and `b` would receive the same datagrams.
+// A host wildcard does not cover network namespace addresses
+static bool udp_addresses_overlap(uint32_t a, uint32_t b)
+{
+    if (a == b) {
+        return true;
+    }
+    if (a == 0) {
+        return !orion_netns_owns_address(b);
+    }
+    return b == 0 && !orion_netns_owns_address(a);
+}
+
+// Called with udp_lock held
+static bool udp_port_in_use(uint32_t ip, uint16_t port)
+{
+    for (int i = 0; i < ORION_UDP_MAX_ENDPOINTS; i++) {
+        if (udp_endpoints[i] && udp_endpoints[i]->local_port == port &&
+            udp_addresses_overlap(udp_endpoints[i]->local_ip, ip)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+orion_udp_endpoint_t *orion_udp_bind(uint32_t local_ip, uint16_t local_port)
+{
+    if (!tcpip_stack.udp_initialized) {
+        return NULL;
+    }
+
+    orion_udp_endpoint_t *endpoint = kmalloc(sizeof(orion_udp_endpoint_t));
+    if (!endpoint) {
+        return NULL;
+    }
+    memset(endpoint, 0, sizeof(orion_udp_endpoint_t));
+    endpoint->local_ip = local_ip;
+    endpoint->multicast_ttl = 1;
+    endpoint->multicast_loop = true;
+    endpoint->ttl = ORION_IP_DEFAULT_TTL;
+
+    spinlock_acquire(&udp_lock);
+    if (local_port == 0) {
+        // Rotate through the ephemeral range, skipping ports in use
+        for (int tries = 0; tries < 65536 - ORION_UDP_EPHEMERAL_FIRST; tries++) {
+            uint16_t candidate = udp_next_ephemeral;
+            udp_next_ephemeral = candidate == 65535 ? ORION_UDP_EPHEMERAL_FIRST : candidate + 1;
+            if (!udp_port_in_use(local_ip, candidate)) {
+                local_port = candidate;
+                break;
+            }
+        }
+    } else if (udp_port_in_use(local_ip, local_port)) {
+        local_port = 0;
+    }
+
+    int slot = -1;
+    for (int i = 0; local_port != 0 && i < ORION_UDP_MAX_ENDPOINTS; i++) {
+        if (!udp_endpoints[i]) {
+            slot = i;
+            break;
+        }
+    }
+    if (slot < 0) {
+        spinlock_release(&udp_lock);
+        kfree(endpoint);
+        return NULL;
+    }
+    endpoint->local_port = local_port;
+    udp_endpoints[slot] = endpoint;
+    spinlock_release(&udp_lock);
+
+    klog_debug(KLOG_CAT_KERNEL, "UDP endpoint bound: %u:%u", local_ip, local_port);
+    return endpoint;
+}
+
+uint16_t orion_udp_local_port(const orion_udp_endpoint_t *endpoint)
+{
+    return endpoint ? endpoint->local_port : 0;
+}
+
+// One's complement sum of 16-bit big-endian words, not yet folded
+static uint32_t udp_sum(uint32_t sum, const uint8_t *data, size_t len)
+{
+    for (size_t i = 0; i + 1 < len; i += 2) {
+        sum += ((uint32_t)data[i] << 8) | data[i + 1];
+    }
+    if (len % 2) {
+        sum += (uint32_t)data[len - 1] << 8;
+    }
+    return sum;
+}
+
+int orion_udp_sendto(orion_udp_endpoint_t *endpoint, uint32_t dst_ip, uint16_t dst_port,
+                     const void *data, size_t len)
+{
+    if (!endpoint || (!data && len) || len > ORION_UDP_MAX_PAYLOAD || dst_port == 0) {
+        return -1;
+    }
+    if (endpoint->dont_fragment && ip_exceeds_mtu(dst_ip, sizeof(orion_udp_header_t) + len)) {
+        TCPIP_STAT_INC(ip_out_discards);
+        return ORION_IP_ERR_MSGSIZE;
+    }
+
+    // IP header, UDP header and payload in one frame for orion_ip_send
+    size_t udp_len = sizeof(orion_udp_header_t) + len;
+    uint8_t *frame = kmalloc(sizeof(orion_ipv4_header_t) + udp_len);
+    if (!frame) {
+        TCPIP_STAT_INC(ip_out_discards);
+        return -1;
+    }
+    orion_udp_header_t *udp_header = (orion_udp_header_t *)(frame + sizeof(orion_ipv4_header_t));
+    udp_header->src_port = htons(endpoint->local_port);
+    udp_header->dst_port = htons(dst_port);
+    udp_header->length = htons((uint16_t)udp_len);
+    udp_header->checksum = 0;
+    if (len) {
+        memcpy(udp_header + 1, data, len);
+    }
+
+    bool multicast = ORION_IN_MULTICAST(dst_ip);
+    uint32_t src_ip = multicast && endpoint->multicast_if ? endpoint->multicast_if : endpoint->local_ip;
+
+    // Checksum over the pseudo-header (addresses, protocol, length) and datagram
+    uint8_t pseudo[12] = {
+        (uint8_t)(src_ip >> 24), (uint8_t)(src_ip >> 16), (uint8_t)(src_ip >> 8), (uint8_t)src_ip,
+        (uint8_t)(dst_ip >> 24), (uint8_t)(dst_ip >> 16), (uint8_t)(dst_ip >> 8), (uint8_t)dst_ip,
+        0, ORION_IP_PROTOCOL_UDP, (uint8_t)(udp_len >> 8), (uint8_t)udp_len,
+    };
+    uint32_t sum = udp_sum(udp_sum(0, pseudo, sizeof(pseudo)), (const uint8_t *)udp_header, udp_len);
+    while (sum >> 16) {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    // A computed zero is sent as all ones; zero means "no checksum"
+    uint16_t checksum = (uint16_t)~sum;
+    udp_header->checksum = htons(checksum ? checksum : 0xFFFF);
+
+    int result = ip_send_packet(src_ip, dst_ip, ORION_IP_PROTOCOL_UDP, frame, udp_len,
+                                multicast ? endpoint->multicast_ttl : endpoint->ttl, endpoint->dont_fragment);
+    if (result == 0) {
+        TCPIP_STAT_INC(udp_out_datagrams);
+    }
+    // Local members of the group get a copy, as if it came back from the wire
+    if (result == 0 && multicast && endpoint->multicast_loop) {
+        orion_udp_input(src_ip, dst_ip, udp_header, udp_len);
+    }
+    kfree(frame);
+    return result;
+}
+
+ssize_t orion_udp_recvfrom(orion_udp_endpoint_t *endpoint, uint32_t *src_ip, uint16_t *src_port,
+                           void *data, size_t len)
+{
+    if (!endpoint || !data) {
+        return -1;
+    }
+
+    spinlock_acquire(&udp_lock);
+    if (endpoint->count == 0) {
+        spinlock_release(&udp_lock);
+        return 0;
+    }
+    udp_datagram_t *datagram = &endpoint->queue[endpoint->head];
+    size_t copied = datagram->len < len ? datagram->len : len;
+    memcpy(data, datagram->data, copied);
+    if (src_ip) *src_ip = datagram->src_ip;
+    if (src_port) *src_port = datagram->src_port;
+    endpoint->head = (endpoint->head + 1) % ORION_UDP_QUEUE_DEPTH;
+    endpoint->count--;
+    spinlock_release(&udp_lock);
+
+    return (ssize_t)copied;
+}
+
+void orion_udp_close(orion_udp_endpoint_t *endpoint)
+{
+    if (!endpoint) {
+        return;
+    }
+
+    spinlock_acquire(&udp_lock);
+    for (int i = 0; i < ORION_UDP_MAX_ENDPOINTS; i++) {
+        if (udp_endpoints[i] == endpoint) {
+            udp_endpoints[i] = NULL;
+        }
+    }
+    spinlock_release(&udp_lock);
+
+    for (int i = 0; i < ORION_UDP_MAX_MEMBERSHIPS; i++) {
+        if (endpoint->groups[i].group) {
+            orion_igmp_leave(endpoint->groups[i].ifaddr, endpoint->groups[i].group);
+        }
+    }
+    kfree(endpoint);
+}
+
+int orion_udp_join_group(orion_udp_endpoint_t *endpoint, uint32_t group, uint32_t ifaddr)
+{
+    if (!endpoint || !ORION_IN_MULTICAST(group)) {
+        return -1;
+    }
+
+    spinlock_acquire(&udp_lock);
+    udp_membership_t *slot = NULL;
+    for (int i = 0; i < ORION_UDP_MAX_MEMBERSHIPS; i++) {
+        udp_membership_t *membership = &endpoint->groups[i];
+        if (membership->group == group && membership->ifaddr == ifaddr) {
+            spinlock_release(&udp_lock);
+            return -1;
+        }
+        if (!membership->group && !slot) {
+            slot = membership;
+        }
+    }
+    if (!slot) {
+        spinlock_release(&udp_lock);
+        return -1;
+    }
+    // Reserved until IGMP took the reference
+    slot->group = group;
+    slot->ifaddr = ifaddr;
+    spinlock_release(&udp_lock);
+
+    if (orion_igmp_join(ifaddr, group) != 0) {
+        spinlock_acquire(&udp_lock);
+        slot->group = 0;
+        spinlock_release(&udp_lock);
+        return -1;
+    }
+    return 0;
+}
+
+int orion_udp_leave_group(orion_udp_endpoint_t *endpoint, uint32_t group, uint32_t ifaddr)
+{
+    if (!endpoint) {
+        return -1;
+    }
+
+    bool found = false;
+    spinlock_acquire(&udp_lock);
+    for (int i = 0; i < ORION_UDP_MAX_MEMBERSHIPS && !found; i++) {
+        udp_membership_t *membership = &endpoint->groups[i];
+        if (membership->group == group && membership->ifaddr == ifaddr) {
+            membership->group = 0;
+            found = true;
+        }
+    }
+    spinlock_release(&udp_lock);
+
+    if (!found) {
+        return -1;
+    }
+    orion_igmp_leave(ifaddr, group);
+    return 0;
+}
+
+void orion_udp_set_multicast_loop(orion_udp_endpoint_t *endpoint, bool enabled)
+{
+    if (endpoint) {
+        endpoint->multicast_loop = enabled;
+    }
+}
+
+void orion_udp_set_multicast_ttl(orion_udp_endpoint_t *endpoint, uint8_t ttl)
+{
+    if (endpoint) {
+        endpoint->multicast_ttl = ttl;
+    }
+}
+
+void orion_udp_set_multicast_if(orion_udp_endpoint_t *endpoint, uint32_t ifaddr)
+{
+    if (endpoint) {
+        endpoint->multicast_if = ifaddr;
+    }
+}
+
+void orion_udp_set_ttl(orion_udp_endpoint_t *endpoint, uint8_t ttl)
+{
+    if (endpoint) {
+        endpoint->ttl = ttl;
+    }
+}
+
+void orion_udp_set_dont_fragment(orion_udp_endpoint_t *endpoint, bool enabled)
+{
+    if (endpoint) {
+        endpoint->dont_fragment = enabled;
+    }
+}
+
+int orion_udp_recverr(orion_udp_endpoint_t *endpoint, orion_udp_error_t *error)
+{
+    if (!endpoint || !error) {
+        return -1;
+    }
+
+    spinlock_acquire(&udp_lock);
+    if (endpoint->error_count == 0) {
+        spinlock_release(&udp_lock);
+        return 0;
+    }
+    *error = endpoint->errors[endpoint->error_head];
+    endpoint->error_head = (endpoint->error_head + 1) % ORION_UDP_ERROR_DEPTH;
+    endpoint->error_count--;
+    spinlock_release(&udp_lock);
+    return 1;
+}
+
+// An ICMP error quoting a datagram we sent goes to the endpoint it left from;
+// the oldest error makes room when the queue is full
+static int udp_error_input(uint32_t reporter, uint8_t type, uint8_t code, uint16_t mtu, uint32_t src_ip,
+                           uint32_t dst_ip, const uint8_t *udp_header)
+{
+    uint16_t src_port = (uint16_t)(udp_header[0] << 8 | udp_header[1]);
+    uint16_t dst_port = (uint16_t)(udp_header[2] << 8 | udp_header[3]);
+
+    int delivered = -1;
+    spinlock_acquire(&udp_lock);
+    for (int i = 0; i < ORION_UDP_MAX_ENDPOINTS; i++) {
+        orion_udp_endpoint_t *endpoint = udp_endpoints[i];
+        if (!endpoint || endpoint->local_port != src_port || (endpoint->local_ip && endpoint->local_ip != src_ip)) {
+            continue;
+        }
+        if (endpoint->error_count == ORION_UDP_ERROR_DEPTH) {
+            endpoint->error_head = (endpoint->error_head + 1) % ORION_UDP_ERROR_DEPTH;
+            endpoint->error_count--;
+        }
+        orion_udp_error_t *error =
+            &endpoint->errors[(endpoint->error_head + endpoint->error_count) % ORION_UDP_ERROR_DEPTH];
+        error->reporter = reporter;
+        error->type = type;
+        error->code = code;
+        error->mtu = mtu;
+        error->dst_ip = dst_ip;
+        error->dst_port = dst_port;
+        endpoint->error_count++;
+        delivered = 0;
+        break;
+    }
+    spinlock_release(&udp_lock);
+    return delivered;
+}
+
+// Called with udp_lock held
+static bool udp_is_member(const orion_udp_endpoint_t *endpoint, uint32_t group)
+{
+    for (int i = 0; i < ORION_UDP_MAX_MEMBERSHIPS; i++) {
+        if (endpoint->groups[i].group == group) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Called with udp_lock held
+static int udp_enqueue(orion_udp_endpoint_t *endpoint, uint32_t src_ip, uint16_t src_port, const void *payload,
+                       size_t len)
+{
+    if (endpoint->count == ORION_UDP_QUEUE_DEPTH) {
+        endpoint->dropped++;
+        return -1;
+    }
+
+    udp_datagram_t *slot = &endpoint->queue[(endpoint->head + endpoint->count) % ORION_UDP_QUEUE_DEPTH];
+    slot->src_ip = src_ip;
+    slot->src_port = src_port;
+    slot->len = (uint16_t)len;
+    memcpy(slot->data, payload, len);
+    endpoint->count++;
+    return 0;
+}
+
+int orion_udp_input(uint32_t src_ip, uint32_t dst_ip, const void *datagram, size_t len)
+{
+    if (!tcpip_stack.udp_initialized || !datagram || len < sizeof(orion_udp_header_t)) {
+        return -1;
+    }
+
+    const orion_udp_header_t *udp_header = (const orion_udp_header_t *)datagram;
+    size_t udp_len = ntohs(udp_header->length);
+    if (udp_len < sizeof(orion_udp_header_t) || udp_len > len) {
+        TCPIP_STAT_INC(udp_in_errors);
+        return -1;
+    }
+    size_t payload_len = udp_len - sizeof(orion_udp_header_t);
+    // Empty datagrams carry nothing a reader could tell from an empty queue
+    if (payload_len == 0 || payload_len > ORION_UDP_MAX_PAYLOAD) {
+        TCPIP_STAT_INC(udp_in_errors);
+        return -1;
+    }
+    uint16_t dst_port = ntohs(udp_header->dst_port);
+    bool multicast = ORION_IN_MULTICAST(dst_ip);
+
+    // A unicast datagram goes to the endpoint bound to its port and address, a
+    // multicast one to every endpoint on the port that joined the group
+    int matched = 0;
+    int delivered = 0;
+    spinlock_acquire(&udp_lock);
+    for (int i = 0; i < ORION_UDP_MAX_ENDPOINTS; i++) {
+        orion_udp_endpoint_t *endpoint = udp_endpoints[i];
+        if (!endpoint || endpoint->local_port != dst_port) {
+            continue;
+        }
+        bool addressed = endpoint->local_ip == 0 ? !orion_netns_owns_address(dst_ip) : endpoint->local_ip == dst_ip;
+        if (multicast ? !udp_is_member(endpoint, dst_ip) : !addressed) {
+            continue;
+        }
+        matched++;
+        if (udp_enqueue(endpoint, src_ip, ntohs(udp_header->src_port), udp_header + 1, payload_len) == 0) {
+            delivered++;
+        }
+        if (!multicast) {
+            break;
+        }
+    }
+    spinlock_release(&udp_lock);
+
+    if (delivered) {
+        TCPIP_STAT_INC(udp_in_datagrams);
+    } else if (matched) {
+        TCPIP_STAT_INC(udp_in_errors);
+    } else {
+        TCPIP_STAT_INC(udp_no_ports);
+    }
+    return delivered ? 0 : -1;
+}
+
 /* ============================================================================
  * ICMP Functions
  * ============================================================================ */
@@ -628,6 +1716,150 @@ int orion_icmp_send(uint32_t src_ip, uint32_t dst_ip, uint8_t type, uint8_t code
     return 0;
 }
 
+int orion_icmp_send_error(uint32_t src_ip, uint32_t dst_ip, uint8_t type, uint8_t code, uint32_t rest,
+                          const void *packet, size_t len)
+{
+    if (!tcpip_stack.icmp_initialized || !packet || len < sizeof(orion_ipv4_header_t)) {
+        return -1;
+    }
+
+    const uint8_t *original = packet;
+    size_t header_len = (size_t)(original[0] & 0x0F) * 4;
+    if (header_len < sizeof(orion_ipv4_header_t) || header_len > len) {
+        return -1;
+    }
+    // Errors about errors could bounce between two routers forever
+    if (original[9] == ORION_IP_PROTOCOL_ICMP && len > header_len) {
+        uint8_t original_type = original[header_len];
+        if (original_type == 3 || original_type == 4 || original_type == 5 || original_type == 11 ||
+            original_type == 12) {
+            return 0;
+        }
+    }
+
+    size_t quoted = header_len + 8 < len ? header_len + 8 : len;
+    size_t icmp_len = 8 + quoted;
+    uint8_t frame[sizeof(orion_ipv4_header_t) + 8 + 60 + 8];
+    uint8_t *icmp = frame + sizeof(orion_ipv4_header_t);
+    icmp[0] = type;
+    icmp[1] = code;
+    icmp[2] = 0;
+    icmp[3] = 0;
+    icmp[4] = (uint8_t)(rest >> 24);
+    icmp[5] = (uint8_t)(rest >> 16);
+    icmp[6] = (uint8_t)(rest >> 8);
+    icmp[7] = (uint8_t)rest;
+    memcpy(icmp + 8, original, quoted);
+    uint16_t checksum = orion_icmp_checksum(icmp, icmp_len);
+    memcpy(icmp + 2, &checksum, sizeof(checksum));
+
+    klog_debug(KLOG_CAT_KERNEL, "ICMP error sent: %u -> %u, type: %d, code: %d",
+               src_ip, dst_ip, type, code);
+    TCPIP_STAT_INC(icmp_out_msgs);
+    return ip_send_ttl(src_ip, dst_ip, ORION_IP_PROTOCOL_ICMP, frame, icmp_len, 64);
+}
+
+int orion_icmp_send_echo(uint32_t src_ip, uint32_t dst_ip, uint8_t type, uint16_t identifier,
+                         uint16_t sequence, uint8_t ttl, bool dont_fragment, const void *data, size_t len)
+{
+    if (!tcpip_stack.icmp_initialized || (len && !data) || len > ORION_PING_MAX_PAYLOAD) {
+        return -1;
+    }
+    if (dont_fragment && ip_exceeds_mtu(dst_ip, 8 + len)) {
+        TCPIP_STAT_INC(ip_out_discards);
+        return ORION_IP_ERR_MSGSIZE;
+    }
+
+    size_t icmp_len = 8 + len;
+    uint8_t *frame = kmalloc(sizeof(orion_ipv4_header_t) + icmp_len);
+    if (!frame) {
+        TCPIP_STAT_INC(ip_out_discards);
+        return -1;
+    }
+    uint8_t *icmp = frame + sizeof(orion_ipv4_header_t);
+    icmp[0] = type;
+    icmp[1] = 0;
+    icmp[2] = 0;
+    icmp[3] = 0;
+    icmp[4] = (uint8_t)(identifier >> 8);
+    icmp[5] = (uint8_t)identifier;
+    icmp[6] = (uint8_t)(sequence >> 8);
+    icmp[7] = (uint8_t)sequence;
+    if (len) {
+        memcpy(icmp + 8, data, len);
+    }
+    uint16_t checksum = orion_icmp_checksum(icmp, icmp_len);
+    memcpy(icmp + 2, &checksum, sizeof(checksum));
+
+    int result = ip_send_packet(src_ip, dst_ip, ORION_IP_PROTOCOL_ICMP, frame, icmp_len, ttl, dont_fragment);
+    if (result == 0) {
+        TCPIP_STAT_INC(icmp_out_msgs);
+    }
+    kfree(frame);
+    return result;
+}
+
+int orion_icmp_input(uint32_t src_ip, uint32_t dst_ip, uint8_t ttl, const void *message, size_t len)
+{
+    if (!tcpip_stack.icmp_initialized || !message || len < 8) {
+        return -1;
+    }
+    const uint8_t *icmp = message;
+    TCPIP_STAT_INC(icmp_in_msgs);
+    if (orion_icmp_checksum(icmp, len) != 0) {
+        TCPIP_STAT_INC(icmp_in_errors);
+        return -1;
+    }
+    uint16_t identifier = (uint16_t)(icmp[4] << 8 | icmp[5]);
+    uint16_t sequence = (uint16_t)(icmp[6] << 8 | icmp[7]);
+
+    switch (icmp[0]) {
+    case ORION_ICMP_ECHO_REQUEST:
+        // Multicast and broadcast pings stay unanswered
+        if (ORION_IN_MULTICAST(dst_ip) || dst_ip == 0xFFFFFFFFU) {
+            return 0;
+        }
+        return orion_icmp_send_echo(dst_ip, src_ip, ORION_ICMP_ECHO_REPLY, identifier, sequence,
+                                    ORION_IP_DEFAULT_TTL, false, icmp + 8, len - 8);
+
+    case ORION_ICMP_ECHO_REPLY:
+        return orion_ping_input(src_ip, icmp[0], icmp[1], identifier, sequence, ttl, 0, icmp + 8, len - 8);
+
+    case ORION_ICMP_DEST_UNREACHABLE:
+    case ORION_ICMP_TIME_EXCEEDED: {
+        // The quoted request: IP header, then its ICMP header and data
+        const uint8_t *quoted = icmp + 8;
+        size_t quoted_len = len - 8;
+        if (quoted_len < sizeof(orion_ipv4_header_t)) {
+            return -1;
+        }
+        size_t header_len = (size_t)(quoted[0] & 0x0F) * 4;
+        if (header_len < sizeof(orion_ipv4_header_t) || quoted_len < header_len + 8) {
+            return 0;
+        }
+        // Fragmentation needed carries the next-hop MTU in the low half of the second word
+        uint16_t mtu = icmp[0] == ORION_ICMP_DEST_UNREACHABLE && icmp[1] == ORION_ICMP_FRAG_NEEDED ? sequence : 0;
+        const uint8_t *request = quoted + header_len;
+        if (quoted[9] == ORION_IP_PROTOCOL_UDP) {
+            uint32_t quoted_src = (uint32_t)quoted[12] << 24 | (uint32_t)quoted[13] << 16 |
+                                  (uint32_t)quoted[14] << 8 | quoted[15];
+            uint32_t quoted_dst = (uint32_t)quoted[16] << 24 | (uint32_t)quoted[17] << 16 |
+                                  (uint32_t)quoted[18] << 8 | quoted[19];
+            return udp_error_input(src_ip, icmp[0], icmp[1], mtu, quoted_src, quoted_dst, request);
+        }
+        if (quoted[9] != ORION_IP_PROTOCOL_ICMP || request[0] != ORION_ICMP_ECHO_REQUEST) {
+            return 0;
+        }
+        return orion_ping_input(src_ip, icmp[0], icmp[1], (uint16_t)(request[4] << 8 | request[5]),
+                                (uint16_t)(request[6] << 8 | request[7]), ttl, mtu, request + 8,
+                                quoted_len - header_len - 8);
+    }
+
+    default:
+        return 0;
+    }
+}
+
 int orion_icmp_ping(uint32_t src_ip, uint32_t dst_ip, uint16_t sequence)
 {
     if (!tcpip_stack.icmp_initialized) {
@@ -649,6 +1881,104 @@ int orion_icmp_ping(uint32_t src_ip, uint32_t dst_ip, uint16_t sequence)
     return result;
 }
 
+/* ============================================================================
+ * Socket Statistics
+ * ============================================================================ */
+
+static uint32_t clamp_u32(uint64_t value)
+{
+    return value > 0xFFFFFFFFULL ? 0xFFFFFFFFU : (uint32_t)value;
+}
+
+// Called with tcp_lock held
+static void tcp_sock_info(const orion_tcp_connection_t *conn, orion_sock_info_t *info)
+{
+    memset(info, 0, sizeof(*info));
+    info->protocol = ORION_IP_PROTOCOL_TCP;
+    info->state = (uint8_t)conn->state;
+    info->congestion_control = (uint8_t)conn->congestion_control;
+    info->local_ip = conn->local_ip;
+    info->remote_ip = conn->remote_ip;
+    info->local_port = conn->local_port;
+    info->remote_port = conn->remote_port;
+    info->recv_queue = clamp_u32(conn->recv_buffer_used);
+    info->send_queue = clamp_u32(conn->send_buffer_used);
+    info->in_flight = orion_tcp_sb_in_flight(&conn->scoreboard);
+    info->retransmissions = clamp_u32(conn->retransmissions);
+    info->timeouts = clamp_u32(conn->timeouts);
+    info->cwnd = conn->cc.cwnd;
+    info->ssthresh = conn->cc.ssthresh;
+    info->rtt_us = clamp_u32(conn->rtt / 1000);
+    info->rttvar_us = clamp_u32(conn->rttvar / 1000);
+    info->rto_ms = clamp_u32(conn->rto / 1000000);
+    info->bytes_sent = conn->bytes_sent;
+    info->bytes_received = conn->bytes_received;
+}
+
+// Called with udp_lock held
+static void udp_sock_info(const orion_udp_endpoint_t *endpoint, orion_sock_info_t *info)
+{
+    memset(info, 0, sizeof(*info));
+    info->protocol = ORION_IP_PROTOCOL_UDP;
+    info->local_ip = endpoint->local_ip;
+    info->local_port = endpoint->local_port;
+    for (uint32_t i = 0; i < endpoint->count; i++) {
+        info->recv_queue += endpoint->queue[(endpoint->head + i) % ORION_UDP_QUEUE_DEPTH].len;
+    }
+    info->dropped = endpoint->dropped;
+}
+
+size_t orion_tcpip_sock_info(size_t first, orion_sock_info_t *info, size_t max)
+{
+    if (!info || max == 0) {
+        return 0;
+    }
+
+    size_t position = 0;
+    size_t copied = 0;
+    spinlock_acquire(&tcp_lock);
+    for (orion_tcp_connection_t *conn = tcp_connections; conn && copied < max; conn = conn->next) {
+        if (position++ >= first) {
+            tcp_sock_info(conn, &info[copied++]);
+        }
+    }
+    spinlock_release(&tcp_lock);
+
+    spinlock_acquire(&udp_lock);
+    for (int i = 0; i < ORION_UDP_MAX_ENDPOINTS && copied < max; i++) {
+        if (udp_endpoints[i] && position++ >= first) {
+            udp_sock_info(udp_endpoints[i], &info[copied++]);
+        }
+    }
+    spinlock_release(&udp_lock);
+    return copied;
+}
+
+void orion_tcpip_get_stats(orion_tcpip_stats_t *stats, uint32_t *tcp_established)
+{
+    if (!stats) {
+        return;
+    }
+    // Every field is a uint64_t counter
+    uint64_t *out = (uint64_t *)stats;
+    const uint64_t *counters = (const uint64_t *)&tcpip_stats;
+    for (size_t i = 0; i < sizeof(*stats) / sizeof(uint64_t); i++) {
+        out[i] = __atomic_load_n(&counters[i], __ATOMIC_RELAXED);
+    }
+
+    if (tcp_established) {
+        uint32_t established = 0;
+        spinlock_acquire(&tcp_lock);
+        for (orion_tcp_connection_t *conn = tcp_connections; conn; conn = conn->next) {
+            if (conn->state == ORION_TCP_STATE_ESTABLISHED || conn->state == ORION_TCP_STATE_CLOSE_WAIT) {
+                established++;
+            }
+        }
+        spinlock_release(&tcp_lock);
+        *tcp_established = established;
+    }
+}
+
 /* ============================================================================
  * NAT Functions
  * ============================================================================ */
@@ -17,6 +17,10 @@
 #include <orion/types.h>
 #include <orion/structures.h>
 #include "network_architecture.h"
+#include "tls_offload.h"
+#include "tcp_options.h"
+#include "tcp_recovery.h"
+#include "tcp_cong.h"
 
 #ifdef __cplusplus
 extern "C"
@@ -119,6 +123,31 @@ extern "C"
         ORION_TCP_STATE_CLOSE         // Connection closing
     } orion_tcp_state_t;
 
+    /* ============================================================================
+     * TCP Buffer Autotuning
+     * ============================================================================ */
+
+    // Buffers start at the default size and are grown towards twice the
+    // bandwidth-delay product, never past the max; under socket memory
+    // pressure (see socket_memory.h) they start at the min and stop growing
+#define ORION_TCP_MSS 1460
+#define ORION_TCP_RMEM_MIN 4096
+#define ORION_TCP_RMEM_DEFAULT 65536
+#define ORION_TCP_RMEM_MAX (4U << 20)
+#define ORION_TCP_WMEM_MIN 4096
+#define ORION_TCP_WMEM_DEFAULT 65536
+#define ORION_TCP_WMEM_MAX (4U << 20)
+#define ORION_TCP_AUTOTUNE_MIN_RTT_NS 1000000ULL       // Shortest measurement period
+#define ORION_TCP_AUTOTUNE_DEFAULT_RTT_NS 100000000ULL // Period before an RTT is known
+
+    /* ============================================================================
+     * TCP Retransmission Timer (RFC 6298)
+     * ============================================================================ */
+
+#define ORION_TCP_RTO_INITIAL_NS 1000000000ULL // Before the first RTT sample
+#define ORION_TCP_RTO_MIN_NS 200000000ULL
+#define ORION_TCP_RTO_MAX_NS 60000000000ULL
+
     /* ============================================================================
      * TCP Connection Structure
      * ============================================================================ */
@@ -139,12 +168,12 @@ extern "C"
         // Flow control
         uint16_t window_size;   // Receive window size
         uint16_t remote_window; // Remote window size
-        uint32_t ssthresh;      // Slow start threshold
-        uint32_t cwnd;          // Congestion window
 
         // Timers
-        uint64_t rtt;            // Round trip time
+        uint64_t rtt;            // Round trip time (ns)
+        uint64_t rttvar;         // Round trip time variation (ns)
         uint64_t rto;            // Retransmission timeout
+        uint64_t rto_deadline;   // Retransmission timer, 0 when unarmed
         uint64_t last_ack_time;  // Last acknowledgment time
         uint64_t last_data_time; // Last data time
 
@@ -156,6 +185,11 @@ extern "C"
         size_t send_buffer_used; // Send buffer used
         size_t recv_buffer_used; // Receive buffer used
 
+        // Receive buffer autotuning
+        size_t rcv_space;        // Most bytes the reader drained in one RTT
+        size_t rcv_copied;       // Bytes drained in the current RTT
+        uint64_t rcv_space_time; // Start of the current RTT (ns)
+
         // Congestion control
         uint32_t snd_una; // Send unacknowledged
         uint32_t snd_nxt; // Send next
@@ -163,6 +197,18 @@ extern "C"
         uint32_t rcv_nxt; // Receive next
         uint32_t rcv_wnd; // Receive window
 
+        orion_tcp_cc_t cc;                // Congestion window and slow start threshold (segments)
+        const orion_tcp_cong_ops_t *cong; // Algorithm in use
+        uint32_t congestion_control;      // Its orion_tcp_cc_algorithm_t
+        bool in_recovery;                 // Repairing losses, window held
+        uint32_t recovery_point;          // snd_nxt when recovery started
+
+        // Options and loss recovery (see tcp_options.h, tcp_recovery.h)
+        orion_tcp_syn_offer_t syn_offer;   // What our SYN offered
+        orion_tcp_negotiated_t options;    // Options in use once synchronized
+        orion_tcp_scoreboard_t scoreboard; // Our segments in flight
+        orion_tcp_rcv_sack_t rcv_sack;     // Out-of-order data we report
+
         // Statistics
         uint64_t bytes_sent;       // Bytes sent
         uint64_t bytes_received;   // Bytes received
@@ -171,6 +217,11 @@ extern "C"
         uint64_t retransmissions;  // Retransmissions
         uint64_t timeouts;         // Timeouts
 
+        // TLS termination (see tls_offload.h)
+        orion_tls_listener_t *tls_listener; // Set on TLS listeners and inherited by accepted connections
+        orion_tls_session_t *tls_session;   // Created on first send/recv of an accepted connection,
+                                            // or with the connection on the client side
+
         // Next connection in list
         struct orion_tcp_connection *next;
     } orion_tcp_connection_t;
@@ -277,6 +328,56 @@ extern "C"
      */
     orion_tcp_connection_t *orion_tcp_listen(uint32_t local_ip, uint16_t local_port, int backlog);
 
+    /**
+     * @brief Terminate TLS in the network server for a listener
+     * @param listener Listener connection
+     * @param certificate_handle Keyring handle of the DER certificate chain
+     * @param key_handle Keyring handle of the Ed25519 private key
+     * @return 0 on success, negative error code on failure
+     *
+     * Connections accepted on the listener then send and receive plaintext
+     * through orion_tcp_send/orion_tcp_recv.
+     */
+    int orion_tcp_listen_tls(orion_tcp_connection_t *listener, uint64_t certificate_handle, uint64_t key_handle);
+
+    /**
+     * @brief Switch an established connection to TLS (STARTTLS)
+     * @param conn Accepted connection that has not used TLS yet
+     * @param certificate_handle Keyring handle of the DER certificate chain
+     * @param key_handle Keyring handle of the Ed25519 private key
+     * @return 0 on success, negative error code on failure
+     *
+     * Plaintext already queued for sending goes out first; the server side
+     * of the handshake starts with the next bytes received.
+     */
+    int orion_tcp_start_tls(orion_tcp_connection_t *conn, uint64_t certificate_handle, uint64_t key_handle);
+
+    /**
+     * @brief Run the client side of TLS on an outgoing connection
+     * @param conn Connection from orion_tcp_connect that carried no data yet
+     * @param anchor_handle Keyring handle of the trusted CA certificate
+     * @param server_name Host name or dotted IPv4 address of the server
+     * @return 0 on success, negative error code on failure
+     *
+     * The handshake starts once the connection is established; until it
+     * completes orion_tcp_send accepts nothing and orion_tcp_recv returns 0.
+     */
+    int orion_tcp_start_tls_client(orion_tcp_connection_t *conn, uint64_t anchor_handle, const char *server_name);
+
+    /**
+     * @brief Export keying material of the TLS session of a connection
+     * @param conn Connection with TLS terminated by the network server
+     * @param label Exporter label
+     * @param out Keying material
+     * @param out_len Bytes wanted, at most ORION_TLS_MAX_EXPORT
+     * @return 0 on success, 1 while the handshake is still running,
+     *         negative error code without TLS or once the session failed
+     *
+     * Like orion_tcp_recv, a call moves the handshake along with what the
+     * peer sent so far.
+     */
+    int orion_tcp_tls_export(orion_tcp_connection_t *conn, const char *label, uint8_t *out, size_t out_len);
+
     /**
      * @brief Send data over TCP connection
      * @param conn TCP connection
@@ -318,10 +419,105 @@ extern "C"
     int orion_tcp_set_congestion_control(orion_tcp_connection_t *conn,
                                          orion_tcp_cc_algorithm_t algorithm);
 
+    /* ============================================================================
+     * TCP Options and Loss Recovery
+     * ============================================================================ */
+
+    /**
+     * @brief Write the options of our SYN or SYN-ACK
+     * @param conn TCP connection
+     * @param buf At least ORION_TCP_OPTIONS_MAX bytes
+     * @param ts_ecr Timestamp of the peer's SYN to echo, 0 on a SYN
+     * @return Bytes written
+     *
+     * Window scaling, SACK and timestamps are offered as the TCP
+     * configuration enables them.
+     */
+    size_t orion_tcp_syn_options(orion_tcp_connection_t *conn, uint8_t *buf, uint32_t ts_ecr);
+
+    /**
+     * @brief Write the options of a segment on a synchronized connection
+     * @param conn TCP connection
+     * @param buf At least ORION_TCP_OPTIONS_MAX bytes
+     * @return Bytes written
+     *
+     * Carries our timestamp and the SACK blocks of out-of-order data; the
+     * segment acknowledges rcv_nxt.
+     */
+    size_t orion_tcp_segment_options(orion_tcp_connection_t *conn, uint8_t *buf);
+
+    /**
+     * @brief Window field of an outgoing segment, scaled as negotiated
+     * @param conn TCP connection
+     * @return Window to put in the header, host order
+     */
+    uint16_t orion_tcp_advertised_window(const orion_tcp_connection_t *conn);
+
+    /**
+     * @brief Process the options and acknowledgment of a received segment
+     * @param conn TCP connection
+     * @param header TCP header, network order, followed by its options
+     * @param header_len Header length including options
+     * @param payload_len Length of the segment's data
+     * @return 0 on success, -1 if the segment must be dropped
+     *
+     * Settles the options on the peer's SYN, rejects old duplicates by
+     * PAWS, updates the RTT estimate, the scoreboard and the congestion
+     * window, and records out-of-order data for the SACK blocks we report.
+     * rcv_nxt is left to the data path.
+     */
+    int orion_tcp_input(orion_tcp_connection_t *conn, const orion_tcp_header_t *header, size_t header_len,
+                        size_t payload_len);
+
+    /**
+     * @brief New data the congestion and peer windows allow to send now
+     * @param conn TCP connection
+     * @return Bytes
+     */
+    size_t orion_tcp_send_quota(const orion_tcp_connection_t *conn);
+
+    /**
+     * @brief Record a segment of new data handed to IP
+     * @param conn TCP connection
+     * @param seq First sequence number
+     * @param len Data length
+     * @return 0 on success, -1 if too many segments are in flight and it must wait
+     */
+    int orion_tcp_output(orion_tcp_connection_t *conn, uint32_t seq, uint32_t len);
+
+    /**
+     * @brief Next lost segment the congestion window allows to retransmit
+     * @param conn TCP connection
+     * @return Segment, or NULL if none
+     */
+    orion_tcp_txseg_t *orion_tcp_next_retransmit(orion_tcp_connection_t *conn);
+
+    /**
+     * @brief Record the retransmission of a segment
+     * @param conn TCP connection
+     * @param seg Segment from orion_tcp_next_retransmit or orion_tcp_timers
+     */
+    void orion_tcp_retransmitted(orion_tcp_connection_t *conn, orion_tcp_txseg_t *seg);
+
+    /**
+     * @brief Run the RTO, RACK reordering and tail loss probe timers
+     * @param conn TCP connection
+     * @param has_new_data Whether unsent data is queued
+     * @param probe_new Set when one new segment must be sent as probe, whatever the congestion window
+     * @return Segment to retransmit as probe, or NULL
+     *
+     * Segments found lost are then offered by orion_tcp_next_retransmit.
+     */
+    orion_tcp_txseg_t *orion_tcp_timers(orion_tcp_connection_t *conn, bool has_new_data, bool *probe_new);
+
     /* ============================================================================
      * IP Functions
      * ============================================================================ */
 
+#define ORION_IP_DEFAULT_TTL 64
+#define ORION_IP_FLAG_DONT_FRAGMENT 0x4000 // In flags_offset, host order
+#define ORION_IP_ERR_MSGSIZE -2           // A don't-fragment packet exceeds the route MTU
+
     /**
      * @brief Initialize IP stack
      * @return 0 on success, negative value on error
@@ -372,6 +568,13 @@ extern "C"
      */
     int orion_ip_remove_route(uint32_t dst_ip, uint32_t dst_mask);
 
+    /**
+     * @brief MTU of the interface a destination is routed through
+     * @param dst_ip Destination IP address
+     * @return MTU, 0 when the destination has no route
+     */
+    uint32_t orion_ip_route_mtu(uint32_t dst_ip);
+
     /* ============================================================================
      * UDP Functions
      * ============================================================================ */
@@ -413,6 +616,140 @@ extern "C"
     ssize_t orion_udp_recv(uint32_t *src_ip, uint16_t *src_port,
                            void *data, size_t len);
 
+    /* ============================================================================
+     * UDP Endpoints
+     * ============================================================================ */
+
+#define ORION_IP_PROTOCOL_UDP 17
+#define ORION_UDP_MAX_ENDPOINTS 64   // Bound endpoints across all clients
+#define ORION_UDP_QUEUE_DEPTH 16     // Datagrams queued per endpoint
+#define ORION_UDP_MAX_PAYLOAD 1472   // Largest payload in one Ethernet frame
+#define ORION_UDP_EPHEMERAL_FIRST 49152
+#define ORION_UDP_MAX_MEMBERSHIPS 8  // Multicast groups joined per endpoint
+#define ORION_UDP_ERROR_DEPTH 8      // ICMP errors queued per endpoint
+
+    // Endpoint bound to a local address; received datagrams are queued until read
+    typedef struct orion_udp_endpoint orion_udp_endpoint_t;
+
+    // ICMP error about a datagram an endpoint sent (IP_RECVERR)
+    typedef struct
+    {
+        uint32_t reporter; // Router or host that sent the error
+        uint8_t type;
+        uint8_t code;
+        uint16_t mtu; // Next-hop MTU of a fragmentation needed error, else 0
+        uint32_t dst_ip;
+        uint16_t dst_port;
+    } orion_udp_error_t;
+
+    /**
+     * @brief Bind a UDP endpoint
+     * @param local_ip Local IP address (0 for any)
+     * @param local_port Local port (0 picks an ephemeral port)
+     * @return Endpoint or NULL if the port is taken or no endpoint is free
+     */
+    orion_udp_endpoint_t *orion_udp_bind(uint32_t local_ip, uint16_t local_port);
+
+    /**
+     * @brief Local port of an endpoint
+     * @param endpoint UDP endpoint
+     * @return Port number
+     */
+    uint16_t orion_udp_local_port(const orion_udp_endpoint_t *endpoint);
+
+    /**
+     * @brief Send a datagram from an endpoint
+     * @param endpoint UDP endpoint
+     * @param dst_ip Destination IP address
+     * @param dst_port Destination port
+     * @param data Payload
+     * @param len Payload length (at most ORION_UDP_MAX_PAYLOAD)
+     * @return 0 on success, ORION_IP_ERR_MSGSIZE if the endpoint does not fragment and the
+     * datagram exceeds the route MTU, another negative value on error
+     */
+    int orion_udp_sendto(orion_udp_endpoint_t *endpoint, uint32_t dst_ip, uint16_t dst_port,
+                         const void *data, size_t len);
+
+    /**
+     * @brief Dequeue the oldest datagram received by an endpoint
+     * @param endpoint UDP endpoint
+     * @param src_ip Source IP address (output)
+     * @param src_port Source port (output)
+     * @param data Payload buffer; longer datagrams are truncated
+     * @param len Buffer length
+     * @return Bytes copied, 0 if nothing is queued, negative value on error
+     */
+    ssize_t orion_udp_recvfrom(orion_udp_endpoint_t *endpoint, uint32_t *src_ip, uint16_t *src_port,
+                               void *data, size_t len);
+
+    /**
+     * @brief Unbind an endpoint and drop its queued datagrams
+     * @param endpoint UDP endpoint
+     */
+    void orion_udp_close(orion_udp_endpoint_t *endpoint);
+
+    /**
+     * @brief Receive a multicast group on an endpoint (IP_ADD_MEMBERSHIP)
+     * @param endpoint UDP endpoint
+     * @param group Group address
+     * @param ifaddr Interface address, 0 for the default interface
+     * @return 0 on success, negative value if already joined or out of memberships
+     */
+    int orion_udp_join_group(orion_udp_endpoint_t *endpoint, uint32_t group, uint32_t ifaddr);
+
+    /**
+     * @brief Stop receiving a multicast group (IP_DROP_MEMBERSHIP)
+     * @param endpoint UDP endpoint
+     * @param group Group address
+     * @param ifaddr Interface address given when joining
+     * @return 0 on success, negative value if the group was not joined
+     */
+    int orion_udp_leave_group(orion_udp_endpoint_t *endpoint, uint32_t group, uint32_t ifaddr);
+
+    /**
+     * @brief Loop multicast sent by an endpoint back to local members (IP_MULTICAST_LOOP, on by default)
+     */
+    void orion_udp_set_multicast_loop(orion_udp_endpoint_t *endpoint, bool enabled);
+
+    /**
+     * @brief Time to live of multicast sent by an endpoint (IP_MULTICAST_TTL, 1 by default)
+     */
+    void orion_udp_set_multicast_ttl(orion_udp_endpoint_t *endpoint, uint8_t ttl);
+
+    /**
+     * @brief Source address of multicast sent by an endpoint (IP_MULTICAST_IF, 0 for its local address)
+     */
+    void orion_udp_set_multicast_if(orion_udp_endpoint_t *endpoint, uint32_t ifaddr);
+
+    /**
+     * @brief Time to live of unicast sent by an endpoint (IP_TTL, ORION_IP_DEFAULT_TTL by default)
+     */
+    void orion_udp_set_ttl(orion_udp_endpoint_t *endpoint, uint8_t ttl);
+
+    /**
+     * @brief Set the don't-fragment flag on datagrams sent by an endpoint (IP_MTU_DISCOVER)
+     */
+    void orion_udp_set_dont_fragment(orion_udp_endpoint_t *endpoint, bool enabled);
+
+    /**
+     * @brief Dequeue the oldest ICMP error about a datagram the endpoint sent
+     * @param endpoint UDP endpoint
+     * @param error Error (output)
+     * @return 1 if an error was dequeued, 0 if none is queued, negative value on error
+     */
+    int orion_udp_recverr(orion_udp_endpoint_t *endpoint, orion_udp_error_t *error);
+
+    /**
+     * @brief Deliver a received UDP datagram to the endpoint bound to its port,
+     * or to every endpoint on the port that joined its multicast group
+     * @param src_ip Source IP address
+     * @param dst_ip Destination IP address
+     * @param datagram UDP header and payload
+     * @param len Datagram length
+     * @return 0 if queued, negative value if dropped
+     */
+    int orion_udp_input(uint32_t src_ip, uint32_t dst_ip, const void *datagram, size_t len);
+
     /* ============================================================================
      * ICMP Functions
      * ============================================================================ */
@@ -442,6 +779,64 @@ extern "C"
     int orion_icmp_send(uint32_t src_ip, uint32_t dst_ip, uint8_t type, uint8_t code,
                         const void *data, size_t len);
 
+#define ORION_IP_PROTOCOL_ICMP 1
+#define ORION_ICMP_ECHO_REPLY 0
+#define ORION_ICMP_DEST_UNREACHABLE 3
+#define ORION_ICMP_FRAG_NEEDED 4 // Destination unreachable code
+#define ORION_ICMP_ECHO_REQUEST 8
+#define ORION_ICMP_TIME_EXCEEDED 11
+
+    /**
+     * @brief Send an ICMP echo request or reply
+     * @param src_ip Source IP address, 0 to let the stack choose
+     * @param dst_ip Destination IP address
+     * @param type ORION_ICMP_ECHO_REQUEST or ORION_ICMP_ECHO_REPLY
+     * @param identifier Echo identifier
+     * @param sequence Echo sequence number
+     * @param ttl IP time to live
+     * @param dont_fragment Set the don't-fragment flag
+     * @param data Echo data
+     * @param len Data length
+     * @return 0 on success, ORION_IP_ERR_MSGSIZE if a don't-fragment request exceeds the
+     * route MTU, another negative value on error
+     */
+    int orion_icmp_send_echo(uint32_t src_ip, uint32_t dst_ip, uint8_t type, uint16_t identifier,
+                             uint16_t sequence, uint8_t ttl, bool dont_fragment, const void *data, size_t len);
+
+    /**
+     * @brief Handle a received ICMP message
+     *
+     * Echo requests are answered; echo replies, and errors quoting an echo
+     * request, go to the echo socket of their identifier (see ping.h).
+     * Errors quoting a UDP datagram are queued on the endpoint that sent it.
+     *
+     * @param src_ip Source IP address
+     * @param dst_ip Destination IP address
+     * @param ttl TTL of the IP packet
+     * @param message ICMP header and data
+     * @param len Message length
+     * @return 0 if handled, negative value if dropped
+     */
+    int orion_icmp_input(uint32_t src_ip, uint32_t dst_ip, uint8_t ttl, const void *message, size_t len);
+
+    /**
+     * @brief Send an ICMP error about a received IPv4 packet
+     *
+     * The message quotes the IP header and first 8 payload bytes of the
+     * packet. Nothing is sent about ICMP error messages.
+     *
+     * @param src_ip Source IP address
+     * @param dst_ip Destination IP address
+     * @param type ICMP type
+     * @param code ICMP code
+     * @param rest Second word of the ICMP header (gateway of a redirect, next-hop MTU)
+     * @param packet Offending packet, starting with its IP header
+     * @param len Packet length
+     * @return 0 on success, negative value on error
+     */
+    int orion_icmp_send_error(uint32_t src_ip, uint32_t dst_ip, uint8_t type, uint8_t code, uint32_t rest,
+                              const void *packet, size_t len);
+
     /**
      * @brief Send ICMP echo request (ping)
      * @param src_ip Source IP address
@@ -451,6 +846,78 @@ extern "C"
      */
     int orion_icmp_ping(uint32_t src_ip, uint32_t dst_ip, uint16_t sequence);
 
+    /* ============================================================================
+     * Socket Statistics
+     * ============================================================================ */
+
+#define ORION_IP_PROTOCOL_TCP 6
+
+    // One TCP connection or UDP endpoint as seen by orion-ss
+    typedef struct
+    {
+        uint8_t protocol;           // ORION_IP_PROTOCOL_TCP or ORION_IP_PROTOCOL_UDP
+        uint8_t state;              // orion_tcp_state_t, 0 for UDP
+        uint8_t congestion_control; // orion_tcp_cc_algorithm_t, TCP only
+        uint32_t local_ip;
+        uint32_t remote_ip; // 0 for listeners and UDP
+        uint16_t local_port;
+        uint16_t remote_port;
+        uint32_t recv_queue; // Bytes received and not read yet
+        uint32_t send_queue; // Bytes queued and not acknowledged yet
+        uint32_t in_flight;  // Segments sent and not acknowledged
+        uint32_t retransmissions;
+        uint32_t timeouts;
+        uint32_t cwnd;     // Segments
+        uint32_t ssthresh; // Segments
+        uint32_t rtt_us;   // Smoothed RTT, 0 before the first sample
+        uint32_t rttvar_us;
+        uint32_t rto_ms;
+        uint64_t bytes_sent;
+        uint64_t bytes_received;
+        uint64_t dropped; // Datagrams dropped on a full queue, UDP only
+    } orion_sock_info_t;
+
+    // Stack-wide counters, named after the SNMP MIB-II objects they follow
+    typedef struct
+    {
+        uint64_t ip_in_receives;
+        uint64_t ip_in_hdr_errors;
+        uint64_t ip_in_delivers;
+        uint64_t ip_out_requests;
+        uint64_t ip_out_discards; // Refused by ORION_IP_ERR_MSGSIZE or without memory
+        uint64_t tcp_active_opens;
+        uint64_t tcp_in_segs;
+        uint64_t tcp_out_segs;
+        uint64_t tcp_retrans_segs;
+        uint64_t tcp_timeouts;
+        uint64_t udp_in_datagrams;
+        uint64_t udp_no_ports;
+        uint64_t udp_in_errors; // Malformed, or dropped on a full queue
+        uint64_t udp_out_datagrams;
+        uint64_t icmp_in_msgs;
+        uint64_t icmp_in_errors;
+        uint64_t icmp_out_msgs;
+    } orion_tcpip_stats_t;
+
+    /**
+     * @brief Copy out the sockets of the stack, TCP connections first, then UDP endpoints
+     * @param first Position of the first socket to copy in that order
+     * @param info Sockets (output)
+     * @param max Capacity of `info`
+     * @return Sockets copied; fewer than `max` once the last one was copied
+     *
+     * Positions shift as sockets open and close, so a walk in several
+     * calls may skip or repeat a socket.
+     */
+    size_t orion_tcpip_sock_info(size_t first, orion_sock_info_t *info, size_t max);
+
+    /**
+     * @brief Copy out the stack-wide counters
+     * @param stats Counters (output)
+     * @param tcp_established TCP connections in ESTABLISHED or CLOSE_WAIT now (output, may be NULL)
+     */
+    void orion_tcpip_get_stats(orion_tcpip_stats_t *stats, uint32_t *tcp_established);
+
     /* ============================================================================
      * Network Address Translation (NAT)
      * ============================================================================ */
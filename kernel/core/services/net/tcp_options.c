@@ -0,0 +1,241 @@
+/*
+ * Orion Operating System - TCP Options Implementation
+ *
+ * Options are laid out the way common stacks do, NOPs padding each to a
+ * 32-bit boundary, so middleboxes that only know those layouts pass them
+ * unchanged. Options that only mean something on a SYN are ignored on
+ * other segments, and a window shift above 14 is read as 14.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "tcp_options.h"
+#include <orion/string.h>
+#include <string.h>
+
+static uint16_t get_be16(const uint8_t *p)
+{
+    return (uint16_t)((p[0] << 8) | p[1]);
+}
+
+static uint32_t get_be32(const uint8_t *p)
+{
+    return ((uint32_t)p[0] << 24) | ((uint32_t)p[1] << 16) | ((uint32_t)p[2] << 8) | p[3];
+}
+
+static void put_be16(uint8_t *p, uint16_t v)
+{
+    p[0] = (uint8_t)(v >> 8);
+    p[1] = (uint8_t)v;
+}
+
+static void put_be32(uint8_t *p, uint32_t v)
+{
+    p[0] = (uint8_t)(v >> 24);
+    p[1] = (uint8_t)(v >> 16);
+    p[2] = (uint8_t)(v >> 8);
+    p[3] = (uint8_t)v;
+}
+
+int orion_tcp_options_parse(const uint8_t *options, size_t len, bool syn, orion_tcp_parsed_options_t *out)
+{
+    memset(out, 0, sizeof(*out));
+
+    size_t i = 0;
+    while (i < len) {
+        uint8_t kind = options[i];
+        if (kind == ORION_TCPOPT_EOL) {
+            break;
+        }
+        if (kind == ORION_TCPOPT_NOP) {
+            i++;
+            continue;
+        }
+        if (i + 1 >= len) {
+            return -1;
+        }
+        uint8_t size = options[i + 1];
+        if (size < 2 || size > len - i) {
+            return -1;
+        }
+
+        const uint8_t *data = options + i + 2;
+        switch (kind) {
+        case ORION_TCPOPT_MSS:
+            if (syn && size == 4) {
+                out->has_mss = true;
+                out->mss = get_be16(data);
+            }
+            break;
+        case ORION_TCPOPT_WSCALE:
+            if (syn && size == 3) {
+                out->has_wscale = true;
+                out->wscale = data[0] > ORION_TCP_MAX_WSCALE ? ORION_TCP_MAX_WSCALE : data[0];
+            }
+            break;
+        case ORION_TCPOPT_SACK_PERM:
+            if (syn && size == 2) {
+                out->sack_permitted = true;
+            }
+            break;
+        case ORION_TCPOPT_SACK:
+            if (!syn && size >= 10 && (size - 2) % 8 == 0) {
+                uint8_t count = (uint8_t)((size - 2) / 8);
+                if (count > ORION_TCP_MAX_SACK_BLOCKS) {
+                    count = ORION_TCP_MAX_SACK_BLOCKS;
+                }
+                for (uint8_t b = 0; b < count; b++) {
+                    out->sack[b].start = get_be32(data + b * 8);
+                    out->sack[b].end = get_be32(data + b * 8 + 4);
+                }
+                out->sack_count = count;
+            }
+            break;
+        case ORION_TCPOPT_TIMESTAMP:
+            if (size == 10) {
+                out->has_timestamp = true;
+                out->ts_val = get_be32(data);
+                out->ts_ecr = get_be32(data + 4);
+            }
+            break;
+        default:
+            break;
+        }
+        i += size;
+    }
+    return 0;
+}
+
+static size_t write_timestamp(uint8_t *buf, uint32_t ts_val, uint32_t ts_ecr)
+{
+    buf[0] = ORION_TCPOPT_TIMESTAMP;
+    buf[1] = 10;
+    put_be32(buf + 2, ts_val);
+    put_be32(buf + 6, ts_ecr);
+    return 10;
+}
+
+size_t orion_tcp_options_write_syn(uint8_t *buf, const orion_tcp_syn_offer_t *offer, uint32_t ts_val,
+                                   uint32_t ts_ecr)
+{
+    size_t n = 0;
+
+    buf[n++] = ORION_TCPOPT_MSS;
+    buf[n++] = 4;
+    put_be16(buf + n, offer->mss);
+    n += 2;
+
+    // SACK-permitted takes the place of the padding in front of the timestamps
+    if (offer->sack_permitted && offer->timestamps) {
+        buf[n++] = ORION_TCPOPT_SACK_PERM;
+        buf[n++] = 2;
+        n += write_timestamp(buf + n, ts_val, ts_ecr);
+    } else if (offer->timestamps) {
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_NOP;
+        n += write_timestamp(buf + n, ts_val, ts_ecr);
+    } else if (offer->sack_permitted) {
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_SACK_PERM;
+        buf[n++] = 2;
+    }
+
+    if (offer->wscale) {
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_WSCALE;
+        buf[n++] = 3;
+        buf[n++] = offer->wscale_shift;
+    }
+    return n;
+}
+
+size_t orion_tcp_options_write(uint8_t *buf, const orion_tcp_negotiated_t *neg, uint32_t ts_val,
+                               const orion_tcp_sack_block_t *blocks, uint8_t count)
+{
+    size_t n = 0;
+
+    if (neg->ts_ok) {
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_NOP;
+        n += write_timestamp(buf + n, ts_val, neg->ts_recent);
+    }
+
+    uint8_t room = neg->ts_ok ? ORION_TCP_TS_SACK_BLOCKS : ORION_TCP_MAX_SACK_BLOCKS;
+    if (count > room) {
+        count = room;
+    }
+    if (neg->sack_ok && count) {
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_NOP;
+        buf[n++] = ORION_TCPOPT_SACK;
+        buf[n++] = (uint8_t)(2 + count * 8);
+        for (uint8_t b = 0; b < count; b++) {
+            put_be32(buf + n, blocks[b].start);
+            put_be32(buf + n + 4, blocks[b].end);
+            n += 8;
+        }
+    }
+    return n;
+}
+
+void orion_tcp_options_negotiate(orion_tcp_negotiated_t *neg, const orion_tcp_syn_offer_t *ours,
+                                 const orion_tcp_parsed_options_t *theirs)
+{
+    memset(neg, 0, sizeof(*neg));
+
+    uint16_t peer_mss = theirs->has_mss ? theirs->mss : ORION_TCP_DEFAULT_MSS;
+    neg->mss = peer_mss < ours->mss ? peer_mss : ours->mss;
+
+    // Scaling is used in both directions or not at all
+    neg->wscale_ok = ours->wscale && theirs->has_wscale;
+    if (neg->wscale_ok) {
+        neg->snd_wscale = theirs->wscale;
+        neg->rcv_wscale = ours->wscale_shift;
+    }
+
+    neg->sack_ok = ours->sack_permitted && theirs->sack_permitted;
+    neg->ts_ok = ours->timestamps && theirs->has_timestamp;
+    if (neg->ts_ok) {
+        neg->ts_recent = theirs->ts_val;
+    }
+}
+
+uint8_t orion_tcp_wscale_for(size_t buffer_size)
+{
+    uint8_t shift = 0;
+    while (shift < ORION_TCP_MAX_WSCALE && (buffer_size >> shift) > 0xFFFF) {
+        shift++;
+    }
+    return shift;
+}
+
+bool orion_tcp_paws_reject(const orion_tcp_negotiated_t *neg, const orion_tcp_parsed_options_t *opts, bool rst)
+{
+    // A reset is accepted whatever its timestamp
+    if (!neg->ts_ok || !opts->has_timestamp || rst) {
+        return false;
+    }
+    return orion_tcp_seq_before(opts->ts_val, neg->ts_recent);
+}
+
+void orion_tcp_ts_update(orion_tcp_negotiated_t *neg, const orion_tcp_parsed_options_t *opts, uint32_t seq)
+{
+    if (!neg->ts_ok || !opts->has_timestamp) {
+        return;
+    }
+    // Only segments covering the last ACK sent may update TS.Recent, so a
+    // delayed ACK echoes the timestamp of the earliest segment it covers
+    if (!orion_tcp_seq_before(opts->ts_val, neg->ts_recent) && !orion_tcp_seq_after(seq, neg->last_ack_sent)) {
+        neg->ts_recent = opts->ts_val;
+    }
+}
+
+uint32_t orion_tcp_ts_now(uint64_t now_ns)
+{
+    return (uint32_t)(now_ns / (1000000000ULL / ORION_TCP_TS_HZ));
+}
@@ -0,0 +1,168 @@
+/*
+ * Orion Operating System - TCP Options
+ *
+ * Parsing and building of the options modern peers negotiate on the SYN:
+ * MSS, window scaling (RFC 7323), SACK-permitted and SACK blocks (RFC
+ * 2018), and timestamps (RFC 7323). An option is in use on a connection
+ * only if both SYNs carried it; window scaling then lets windows exceed
+ * 64 KiB, and timestamps give an RTT sample on every ACK and protect
+ * against wrapped sequence numbers (PAWS).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_TCP_OPTIONS_H
+#define ORION_TCP_OPTIONS_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Option kinds
+#define ORION_TCPOPT_EOL 0
+#define ORION_TCPOPT_NOP 1
+#define ORION_TCPOPT_MSS 2
+#define ORION_TCPOPT_WSCALE 3
+#define ORION_TCPOPT_SACK_PERM 4
+#define ORION_TCPOPT_SACK 5
+#define ORION_TCPOPT_TIMESTAMP 8
+
+#define ORION_TCP_OPTIONS_MAX 40    // Option space of a header
+#define ORION_TCP_MAX_WSCALE 14     // Largest shift RFC 7323 allows
+#define ORION_TCP_MAX_SACK_BLOCKS 4 // Blocks fitting without timestamps
+#define ORION_TCP_TS_SACK_BLOCKS 3  // Blocks fitting next to timestamps
+#define ORION_TCP_DEFAULT_MSS 536   // Peer MSS when the SYN carries none
+#define ORION_TCP_TS_HZ 1000        // Timestamp clock, 1 ms ticks
+
+    typedef struct
+    {
+        uint32_t start; // First sequence number of the block
+        uint32_t end;   // Sequence number following the block
+    } orion_tcp_sack_block_t;
+
+    // Options found on one segment
+    typedef struct
+    {
+        bool has_mss;
+        bool has_wscale;
+        bool sack_permitted;
+        bool has_timestamp;
+        uint16_t mss;
+        uint8_t wscale;
+        uint8_t sack_count;
+        uint32_t ts_val;
+        uint32_t ts_ecr;
+        orion_tcp_sack_block_t sack[ORION_TCP_MAX_SACK_BLOCKS];
+    } orion_tcp_parsed_options_t;
+
+    // What our SYN or SYN-ACK offers
+    typedef struct
+    {
+        uint16_t mss;
+        bool wscale;
+        uint8_t wscale_shift; // Shift we apply to the windows we advertise
+        bool sack_permitted;
+        bool timestamps;
+    } orion_tcp_syn_offer_t;
+
+    // Options in use on a connection
+    typedef struct
+    {
+        uint16_t mss;           // Largest segment the peer takes
+        bool wscale_ok;         // Both sides sent window scale
+        uint8_t snd_wscale;     // Shift of the windows the peer advertises
+        uint8_t rcv_wscale;     // Shift of the windows we advertise
+        bool sack_ok;           // Both sides sent SACK-permitted
+        bool ts_ok;             // Both sides sent timestamps
+        uint32_t ts_recent;     // Latest TSval to echo
+        uint32_t last_ack_sent; // RCV.NXT of our last ACK, for the TS.Recent rule
+    } orion_tcp_negotiated_t;
+
+    /**
+     * @brief Parse the options of a segment
+     * @param options Option bytes following the fixed header
+     * @param len Option length
+     * @param syn Whether the segment is a SYN; SYN-only options elsewhere are ignored
+     * @param out Options found
+     * @return 0, or -1 if an option runs past the end or has a bad length
+     */
+    int orion_tcp_options_parse(const uint8_t *options, size_t len, bool syn, orion_tcp_parsed_options_t *out);
+
+    /**
+     * @brief Write the options of a SYN or SYN-ACK
+     * @param buf At least ORION_TCP_OPTIONS_MAX bytes
+     * @param offer Options offered
+     * @param ts_val Our timestamp
+     * @param ts_ecr Timestamp echoed, 0 on a SYN
+     * @return Bytes written, a multiple of 4
+     */
+    size_t orion_tcp_options_write_syn(uint8_t *buf, const orion_tcp_syn_offer_t *offer, uint32_t ts_val,
+                                       uint32_t ts_ecr);
+
+    /**
+     * @brief Write the options of a segment on a synchronized connection
+     * @param buf At least ORION_TCP_OPTIONS_MAX bytes
+     * @param neg Options in use
+     * @param ts_val Our timestamp
+     * @param blocks SACK blocks to report, most recent first
+     * @param count Number of blocks; those not fitting are left out
+     * @return Bytes written, a multiple of 4
+     */
+    size_t orion_tcp_options_write(uint8_t *buf, const orion_tcp_negotiated_t *neg, uint32_t ts_val,
+                                   const orion_tcp_sack_block_t *blocks, uint8_t count);
+
+    /**
+     * @brief Settle the options of a connection from both SYNs
+     * @param neg Options in use
+     * @param ours What we offered
+     * @param theirs What the peer's SYN carried
+     */
+    void orion_tcp_options_negotiate(orion_tcp_negotiated_t *neg, const orion_tcp_syn_offer_t *ours,
+                                     const orion_tcp_parsed_options_t *theirs);
+
+    /**
+     * @brief Smallest window shift letting a window of buffer_size bytes be advertised
+     */
+    uint8_t orion_tcp_wscale_for(size_t buffer_size);
+
+    /**
+     * @brief PAWS check (RFC 7323 section 5)
+     * @return true if the segment carries a timestamp older than TS.Recent and must be dropped
+     */
+    bool orion_tcp_paws_reject(const orion_tcp_negotiated_t *neg, const orion_tcp_parsed_options_t *opts,
+                               bool rst);
+
+    /**
+     * @brief Record the TSval of an acceptable segment starting at or before our last ACK
+     */
+    void orion_tcp_ts_update(orion_tcp_negotiated_t *neg, const orion_tcp_parsed_options_t *opts, uint32_t seq);
+
+    /**
+     * @brief Our timestamp clock
+     * @param now_ns Monotonic time
+     */
+    uint32_t orion_tcp_ts_now(uint64_t now_ns);
+
+    // Sequence number comparison modulo 2^32
+    static inline bool orion_tcp_seq_before(uint32_t a, uint32_t b)
+    {
+        return (int32_t)(a - b) < 0;
+    }
+
+    static inline bool orion_tcp_seq_after(uint32_t a, uint32_t b)
+    {
+        return (int32_t)(b - a) < 0;
+    }
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_TCP_OPTIONS_H
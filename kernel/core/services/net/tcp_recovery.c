@@ -0,0 +1,473 @@
+/*
+ * Orion Operating System - TCP SACK Scoreboard and RACK-TLP Implementation
+ *
+ * A retransmitted segment leaves the lost count and is back in flight;
+ * RACK may mark it lost again once a segment sent after the
+ * retransmission is delivered. RTT samples from retransmissions are only
+ * taken when they cannot belong to the original transmission.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "tcp_recovery.h"
+#include <orion/mm.h>
+#include <orion/string.h>
+#include <string.h>
+
+/* ============================================================================
+ * Scoreboard
+ * ============================================================================ */
+
+static orion_tcp_txseg_t *sb_at(orion_tcp_scoreboard_t *sb, uint32_t offset)
+{
+    return &sb->segs[(sb->head + offset) % sb->capacity];
+}
+
+int orion_tcp_sb_init(orion_tcp_scoreboard_t *sb, uint32_t capacity)
+{
+    memset(sb, 0, sizeof(*sb));
+    sb->capacity = capacity ? capacity : ORION_TCP_SCOREBOARD_DEFAULT;
+    sb->segs = (orion_tcp_txseg_t *)kmalloc(sb->capacity * sizeof(orion_tcp_txseg_t));
+    if (!sb->segs) {
+        return -1;
+    }
+    sb->reo_wnd_mult = 1;
+    return 0;
+}
+
+void orion_tcp_sb_destroy(orion_tcp_scoreboard_t *sb)
+{
+    if (sb->segs) {
+        kfree(sb->segs);
+    }
+    memset(sb, 0, sizeof(*sb));
+}
+
+int orion_tcp_sb_on_send(orion_tcp_scoreboard_t *sb, uint32_t seq, uint32_t len, uint64_t now_ns)
+{
+    if (!sb->segs || sb->count == sb->capacity || len == 0) {
+        return -1;
+    }
+    orion_tcp_txseg_t *seg = sb_at(sb, sb->count++);
+    seg->seq = seq;
+    seg->end = seq + len;
+    seg->xmit_ns = now_ns;
+    seg->flags = 0;
+    return 0;
+}
+
+orion_tcp_txseg_t *orion_tcp_sb_next_lost(orion_tcp_scoreboard_t *sb)
+{
+    if (sb->lost_out == 0) {
+        return NULL;
+    }
+    for (uint32_t i = 0; i < sb->count; i++) {
+        orion_tcp_txseg_t *seg = sb_at(sb, i);
+        if (seg->flags & ORION_TCP_SEG_LOST) {
+            return seg;
+        }
+    }
+    return NULL;
+}
+
+void orion_tcp_sb_on_retransmit(orion_tcp_scoreboard_t *sb, orion_tcp_txseg_t *seg, uint64_t now_ns)
+{
+    if (seg->flags & ORION_TCP_SEG_LOST) {
+        seg->flags &= ~ORION_TCP_SEG_LOST;
+        sb->lost_out--;
+    }
+    if (!(seg->flags & ORION_TCP_SEG_RETRANS)) {
+        seg->flags |= ORION_TCP_SEG_RETRANS;
+        sb->retrans_out++;
+    }
+    seg->xmit_ns = now_ns;
+}
+
+uint32_t orion_tcp_sb_in_flight(const orion_tcp_scoreboard_t *sb)
+{
+    return sb->count - sb->sacked_out - sb->lost_out;
+}
+
+// Offset of the first segment ending after seq
+static uint32_t sb_search(orion_tcp_scoreboard_t *sb, uint32_t seq)
+{
+    uint32_t lo = 0;
+    uint32_t hi = sb->count;
+    while (lo < hi) {
+        uint32_t mid = lo + (hi - lo) / 2;
+        if (orion_tcp_seq_after(sb_at(sb, mid)->end, seq)) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    return lo;
+}
+
+static uint32_t sb_find(orion_tcp_scoreboard_t *sb, uint32_t seq)
+{
+    // Blocks usually grow at the edge the last ACK reported
+    uint32_t hint = sb->hint;
+    if (hint < sb->count && orion_tcp_seq_after(sb_at(sb, hint)->end, seq) &&
+        (hint == 0 || !orion_tcp_seq_after(sb_at(sb, hint - 1)->end, seq))) {
+        return hint;
+    }
+    return sb_search(sb, seq);
+}
+
+/* ============================================================================
+ * RACK
+ * ============================================================================ */
+
+static bool rack_sent_after(uint64_t t1, uint32_t seq1, uint64_t t2, uint32_t seq2)
+{
+    return t1 > t2 || (t1 == t2 && orion_tcp_seq_after(seq1, seq2));
+}
+
+// A segment was delivered: it becomes the RACK reference if it was sent last
+static void rack_update(orion_tcp_scoreboard_t *sb, const orion_tcp_txseg_t *seg, uint64_t now_ns,
+                        orion_tcp_ack_result_t *result)
+{
+    uint64_t rtt = now_ns - seg->xmit_ns;
+
+    // An ACK quicker than the path allows was for the original transmission
+    if ((seg->flags & ORION_TCP_SEG_RETRANS) && sb->min_rtt_ns && rtt < sb->min_rtt_ns) {
+        return;
+    }
+
+    if (!sb->min_rtt_ns || rtt < sb->min_rtt_ns) {
+        sb->min_rtt_ns = rtt;
+    }
+    if (rack_sent_after(seg->xmit_ns, seg->end, sb->rack_xmit_ns, sb->rack_end_seq)) {
+        sb->rack_xmit_ns = seg->xmit_ns;
+        sb->rack_end_seq = seg->end;
+        sb->rack_rtt_ns = rtt;
+        result->rtt_ns = rtt;
+    }
+}
+
+static uint64_t rack_reo_wnd(const orion_tcp_scoreboard_t *sb)
+{
+    // Without reordering seen, losses are declared as soon as three
+    // segments are SACKed above a hole, as with duplicate ACKs
+    if (!sb->reordering_seen && sb->sacked_out >= 3) {
+        return 0;
+    }
+    uint64_t wnd = sb->min_rtt_ns / 4 * sb->reo_wnd_mult;
+    return wnd < sb->rack_rtt_ns ? wnd : sb->rack_rtt_ns;
+}
+
+static uint32_t rack_detect_loss(orion_tcp_scoreboard_t *sb, uint64_t now_ns)
+{
+    uint64_t reo_wnd = rack_reo_wnd(sb);
+    uint64_t timeout = 0;
+    uint32_t lost = 0;
+
+    sb->reo_deadline_ns = 0;
+    if (!sb->rack_xmit_ns) {
+        return 0;
+    }
+
+    for (uint32_t i = 0; i < sb->count; i++) {
+        orion_tcp_txseg_t *seg = sb_at(sb, i);
+        if (seg->flags & (ORION_TCP_SEG_SACKED | ORION_TCP_SEG_LOST)) {
+            continue;
+        }
+        if (!rack_sent_after(sb->rack_xmit_ns, sb->rack_end_seq, seg->xmit_ns, seg->end)) {
+            // Original transmissions are in time order: none further was
+            // sent before the reference
+            if (!(seg->flags & ORION_TCP_SEG_RETRANS)) {
+                break;
+            }
+            continue;
+        }
+
+        uint64_t expiry = seg->xmit_ns + sb->rack_rtt_ns + reo_wnd;
+        if (expiry <= now_ns) {
+            if (seg->flags & ORION_TCP_SEG_RETRANS) {
+                seg->flags &= ~ORION_TCP_SEG_RETRANS;
+                sb->retrans_out--;
+            }
+            seg->flags |= ORION_TCP_SEG_LOST;
+            sb->lost_out++;
+            lost++;
+        } else if (expiry - now_ns > timeout) {
+            timeout = expiry - now_ns;
+        }
+    }
+
+    if (timeout) {
+        sb->reo_deadline_ns = now_ns + timeout;
+    }
+    sb->rack_losses += lost;
+    return lost;
+}
+
+/* ============================================================================
+ * ACK processing
+ * ============================================================================ */
+
+static void sb_mark_sacked(orion_tcp_scoreboard_t *sb, uint32_t start, uint32_t end, uint64_t now_ns,
+                           orion_tcp_ack_result_t *result)
+{
+    uint32_t i = sb_find(sb, start);
+    for (; i < sb->count; i++) {
+        orion_tcp_txseg_t *seg = sb_at(sb, i);
+        if (!orion_tcp_seq_before(seg->seq, end)) {
+            break;
+        }
+        // Only whole segments count as delivered
+        if (orion_tcp_seq_before(seg->seq, start) || orion_tcp_seq_after(seg->end, end)) {
+            continue;
+        }
+        if (seg->flags & ORION_TCP_SEG_SACKED) {
+            continue;
+        }
+
+        if (sb->sacked_out && orion_tcp_seq_before(seg->end, sb->highest_sacked) &&
+            !(seg->flags & ORION_TCP_SEG_RETRANS)) {
+            sb->reordering_seen = true;
+        }
+        rack_update(sb, seg, now_ns, result);
+
+        if (seg->flags & ORION_TCP_SEG_LOST) {
+            sb->lost_out--;
+        }
+        if (seg->flags & ORION_TCP_SEG_RETRANS) {
+            sb->retrans_out--;
+        }
+        seg->flags = ORION_TCP_SEG_SACKED;
+        sb->sacked_out++;
+        result->delivered++;
+
+        if (sb->sacked_out == 1 || orion_tcp_seq_after(seg->end, sb->highest_sacked)) {
+            sb->highest_sacked = seg->end;
+        }
+    }
+    sb->hint = i;
+}
+
+static void sb_cumulative_ack(orion_tcp_scoreboard_t *sb, uint32_t ack, uint64_t now_ns,
+                              orion_tcp_ack_result_t *result)
+{
+    uint32_t removed = 0;
+    while (sb->count > 0) {
+        orion_tcp_txseg_t *seg = sb_at(sb, 0);
+        if (orion_tcp_seq_after(seg->end, ack)) {
+            // Partially acknowledged: keep the rest in flight
+            if (orion_tcp_seq_after(ack, seg->seq)) {
+                seg->seq = ack;
+            }
+            break;
+        }
+
+        if (seg->flags & ORION_TCP_SEG_SACKED) {
+            sb->sacked_out--;
+        } else {
+            if (sb->sacked_out && orion_tcp_seq_before(seg->end, sb->highest_sacked) &&
+                !(seg->flags & ORION_TCP_SEG_RETRANS)) {
+                sb->reordering_seen = true;
+            }
+            rack_update(sb, seg, now_ns, result);
+            result->delivered++;
+        }
+        if (seg->flags & ORION_TCP_SEG_LOST) {
+            sb->lost_out--;
+        }
+        if (seg->flags & ORION_TCP_SEG_RETRANS) {
+            sb->retrans_out--;
+        }
+
+        sb->head = (sb->head + 1) % sb->capacity;
+        sb->count--;
+        removed++;
+    }
+    sb->hint = sb->hint > removed ? sb->hint - removed : 0;
+}
+
+// RFC 2883: a first block below the ACK or inside the second reports a duplicate
+static bool sack_is_dsack(uint32_t ack, const orion_tcp_sack_block_t *blocks, uint8_t count)
+{
+    if (count == 0) {
+        return false;
+    }
+    if (!orion_tcp_seq_after(blocks[0].end, ack)) {
+        return true;
+    }
+    return count > 1 && !orion_tcp_seq_before(blocks[0].start, blocks[1].start) &&
+           !orion_tcp_seq_after(blocks[0].end, blocks[1].end);
+}
+
+void orion_tcp_sb_on_ack(orion_tcp_scoreboard_t *sb, uint32_t ack, const orion_tcp_sack_block_t *blocks,
+                         uint8_t count, uint64_t now_ns, orion_tcp_ack_result_t *result)
+{
+    memset(result, 0, sizeof(*result));
+    if (!sb->segs) {
+        return;
+    }
+
+    uint8_t first = 0;
+    if (sack_is_dsack(ack, blocks, count)) {
+        // A retransmission was not needed: allow for more reordering
+        result->dsack = true;
+        sb->dsacks++;
+        sb->reordering_seen = true;
+        if (sb->reo_wnd_mult < ORION_TCP_RACK_REO_WND_MAX_MULT) {
+            sb->reo_wnd_mult++;
+        }
+        first = 1;
+    }
+
+    sb_cumulative_ack(sb, ack, now_ns, result);
+
+    for (uint8_t b = first; b < count; b++) {
+        uint32_t start = blocks[b].start;
+        uint32_t end = blocks[b].end;
+        // Ignore blocks that are inverted or not above the ACK
+        if (!orion_tcp_seq_after(end, start) || !orion_tcp_seq_after(end, ack)) {
+            continue;
+        }
+        if (orion_tcp_seq_before(start, ack)) {
+            start = ack;
+        }
+        sb_mark_sacked(sb, start, end, now_ns, result);
+    }
+    if (sb->sacked_out == 0) {
+        sb->highest_sacked = ack;
+    }
+
+    // The probe is answered once the ACK covers it
+    if (sb->tlp_outstanding && !orion_tcp_seq_before(ack, sb->tlp_end_seq)) {
+        sb->tlp_outstanding = false;
+        // A retransmitted probe that was not duplicated repaired a loss
+        if (sb->tlp_retrans && !result->dsack) {
+            result->tlp_loss = true;
+            sb->tlp_recoveries++;
+        }
+    }
+
+    result->newly_lost = rack_detect_loss(sb, now_ns);
+}
+
+uint32_t orion_tcp_sb_on_reo_timeout(orion_tcp_scoreboard_t *sb, uint64_t now_ns)
+{
+    return rack_detect_loss(sb, now_ns);
+}
+
+uint32_t orion_tcp_sb_on_rto(orion_tcp_scoreboard_t *sb)
+{
+    uint32_t lost = 0;
+    for (uint32_t i = 0; i < sb->count; i++) {
+        orion_tcp_txseg_t *seg = sb_at(sb, i);
+        if (seg->flags & (ORION_TCP_SEG_SACKED | ORION_TCP_SEG_LOST)) {
+            continue;
+        }
+        if (seg->flags & ORION_TCP_SEG_RETRANS) {
+            seg->flags &= ~ORION_TCP_SEG_RETRANS;
+            sb->retrans_out--;
+        }
+        seg->flags |= ORION_TCP_SEG_LOST;
+        sb->lost_out++;
+        lost++;
+    }
+    sb->reo_deadline_ns = 0;
+    sb->tlp_deadline_ns = 0;
+    sb->tlp_outstanding = false;
+    return lost;
+}
+
+/* ============================================================================
+ * Tail loss probe
+ * ============================================================================ */
+
+void orion_tcp_tlp_arm(orion_tcp_scoreboard_t *sb, uint64_t srtt_ns, uint64_t rto_ns, uint64_t now_ns)
+{
+    // One probe per flight, and none while losses are being repaired
+    if (sb->count == 0 || sb->tlp_outstanding || sb->lost_out || sb->retrans_out) {
+        sb->tlp_deadline_ns = 0;
+        return;
+    }
+
+    uint64_t pto;
+    if (srtt_ns) {
+        pto = 2 * srtt_ns;
+        // A lone segment may wait for the peer's delayed ACK
+        if (sb->count == 1) {
+            pto += ORION_TCP_TLP_DELAYED_ACK_NS;
+        }
+    } else {
+        pto = 1000000000ULL;
+    }
+    if (pto < ORION_TCP_TLP_MIN_NS) {
+        pto = ORION_TCP_TLP_MIN_NS;
+    }
+    if (rto_ns && pto > rto_ns) {
+        pto = rto_ns;
+    }
+    sb->tlp_deadline_ns = now_ns + pto;
+}
+
+orion_tcp_txseg_t *orion_tcp_tlp_fire(orion_tcp_scoreboard_t *sb, bool has_new_data, uint32_t snd_nxt)
+{
+    sb->tlp_deadline_ns = 0;
+    if (sb->count == 0) {
+        return NULL;
+    }
+
+    sb->tlp_probes++;
+    sb->tlp_outstanding = true;
+    sb->tlp_retrans = !has_new_data;
+    sb->tlp_end_seq = snd_nxt;
+    if (has_new_data) {
+        return NULL;
+    }
+    return sb_at(sb, sb->count - 1);
+}
+
+/* ============================================================================
+ * Receive side blocks
+ * ============================================================================ */
+
+void orion_tcp_rcv_sack_add(orion_tcp_rcv_sack_t *rs, uint32_t start, uint32_t end)
+{
+    orion_tcp_sack_block_t merged = {start, end};
+    orion_tcp_sack_block_t kept[ORION_TCP_MAX_SACK_BLOCKS];
+    uint8_t count = 0;
+
+    // Absorb every block the new data touches
+    for (uint8_t i = 0; i < rs->count; i++) {
+        orion_tcp_sack_block_t *b = &rs->blocks[i];
+        if (orion_tcp_seq_after(b->start, merged.end) || orion_tcp_seq_before(b->end, merged.start)) {
+            kept[count++] = *b;
+            continue;
+        }
+        if (orion_tcp_seq_before(b->start, merged.start)) {
+            merged.start = b->start;
+        }
+        if (orion_tcp_seq_after(b->end, merged.end)) {
+            merged.end = b->end;
+        }
+    }
+
+    // The block holding the latest data goes first; the oldest falls off
+    rs->blocks[0] = merged;
+    rs->count = 1;
+    for (uint8_t i = 0; i < count && rs->count < ORION_TCP_MAX_SACK_BLOCKS; i++) {
+        rs->blocks[rs->count++] = kept[i];
+    }
+}
+
+void orion_tcp_rcv_sack_advance(orion_tcp_rcv_sack_t *rs, uint32_t rcv_nxt)
+{
+    uint8_t count = 0;
+    for (uint8_t i = 0; i < rs->count; i++) {
+        if (orion_tcp_seq_after(rs->blocks[i].end, rcv_nxt)) {
+            rs->blocks[count++] = rs->blocks[i];
+        }
+    }
+    rs->count = count;
+}
@@ -0,0 +1,192 @@
+/*
+ * Orion Operating System - TCP SACK Scoreboard and RACK-TLP
+ *
+ * Loss recovery of a sending connection. The scoreboard keeps every
+ * segment in flight in sequence order, with the time it was last sent
+ * and whether the peer selectively acknowledged it. SACK blocks are
+ * located by a hint at the segment the previous block ended on, falling
+ * back to a binary search, so an ACK costs little more than the segments
+ * it newly covers.
+ *
+ * Losses are detected by time rather than by counting duplicate ACKs
+ * (RACK, RFC 8985): once a segment sent later has been delivered, an
+ * earlier one still missing after an RTT plus a reordering window is
+ * lost. The window starts at a quarter of the minimum RTT and widens
+ * when D-SACKs show a retransmission was spurious. A tail loss probe
+ * (TLP) sent two RTTs after the last transmission makes the peer
+ * acknowledge, so losses at the end of a flight are found without
+ * waiting for the RTO.
+ *
+ * The receive side keeps the SACK blocks a connection reports for data
+ * that arrived out of order, most recent first (RFC 2018).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_TCP_RECOVERY_H
+#define ORION_TCP_RECOVERY_H
+
+#include <orion/types.h>
+#include "tcp_options.h"
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_TCP_SCOREBOARD_DEFAULT 1024         // Segments in flight tracked per connection
+#define ORION_TCP_RACK_REO_WND_MAX_MULT 16        // Widest reordering window, in min RTT / 4
+#define ORION_TCP_TLP_MIN_NS 10000000ULL          // Shortest probe timeout
+#define ORION_TCP_TLP_DELAYED_ACK_NS 200000000ULL // Allowance when one segment is in flight
+
+// Segment flags
+#define ORION_TCP_SEG_SACKED 0x01  // Selectively acknowledged
+#define ORION_TCP_SEG_LOST 0x02    // Marked lost, waiting for retransmission
+#define ORION_TCP_SEG_RETRANS 0x04 // Retransmitted, not delivered yet
+
+    typedef struct
+    {
+        uint32_t seq;     // First sequence number
+        uint32_t end;     // Sequence number following the segment
+        uint64_t xmit_ns; // Last (re)transmission
+        uint32_t flags;
+    } orion_tcp_txseg_t;
+
+    typedef struct
+    {
+        // Segments in flight, oldest first, in a ring
+        orion_tcp_txseg_t *segs;
+        uint32_t capacity;
+        uint32_t head;
+        uint32_t count;
+        uint32_t hint; // Offset from head where the last SACK block ended
+
+        uint32_t sacked_out;     // Segments SACKed
+        uint32_t lost_out;       // Segments lost and not retransmitted
+        uint32_t retrans_out;    // Retransmissions in flight
+        uint32_t highest_sacked; // End of the highest SACKed segment
+
+        // RACK: the most recently sent segment known delivered
+        uint64_t rack_xmit_ns;
+        uint32_t rack_end_seq;
+        uint64_t rack_rtt_ns;
+        uint64_t min_rtt_ns;
+        uint32_t reo_wnd_mult;
+        bool reordering_seen;
+        uint64_t reo_deadline_ns; // Reordering timer, 0 when unarmed
+
+        // TLP
+        uint64_t tlp_deadline_ns; // Probe timer, 0 when unarmed
+        uint32_t tlp_end_seq;     // snd_nxt when the probe went out
+        bool tlp_outstanding;
+        bool tlp_retrans; // The probe was a retransmission
+
+        // Statistics
+        uint64_t dsacks;
+        uint64_t rack_losses;
+        uint64_t tlp_probes;
+        uint64_t tlp_recoveries; // Probes that repaired a tail loss
+    } orion_tcp_scoreboard_t;
+
+    // What an ACK did to the scoreboard
+    typedef struct
+    {
+        uint32_t delivered;  // Segments newly acknowledged or SACKed
+        uint32_t newly_lost; // Segments RACK marked lost
+        uint64_t rtt_ns;     // RTT of the most recently sent segment delivered, 0 if none
+        bool dsack;          // The first block reported a duplicate
+        bool tlp_loss;       // A retransmitting probe repaired a loss the sender has to react to
+    } orion_tcp_ack_result_t;
+
+    // SACK blocks a receiver reports, most recent first
+    typedef struct
+    {
+        orion_tcp_sack_block_t blocks[ORION_TCP_MAX_SACK_BLOCKS];
+        uint8_t count;
+    } orion_tcp_rcv_sack_t;
+
+    /**
+     * @brief Allocate a scoreboard
+     * @param capacity Segments in flight tracked, 0 for the default
+     * @return 0 or -1 if out of memory
+     */
+    int orion_tcp_sb_init(orion_tcp_scoreboard_t *sb, uint32_t capacity);
+    void orion_tcp_sb_destroy(orion_tcp_scoreboard_t *sb);
+
+    /**
+     * @brief Record a new segment sent at the end of the flight
+     * @return 0, or -1 if the scoreboard is full and the segment must not be sent
+     */
+    int orion_tcp_sb_on_send(orion_tcp_scoreboard_t *sb, uint32_t seq, uint32_t len, uint64_t now_ns);
+
+    /**
+     * @brief Oldest segment marked lost and not retransmitted yet, NULL if none
+     */
+    orion_tcp_txseg_t *orion_tcp_sb_next_lost(orion_tcp_scoreboard_t *sb);
+
+    /**
+     * @brief Record the retransmission of a segment
+     */
+    void orion_tcp_sb_on_retransmit(orion_tcp_scoreboard_t *sb, orion_tcp_txseg_t *seg, uint64_t now_ns);
+
+    /**
+     * @brief Process the cumulative ACK and SACK blocks of a segment, then run RACK
+     * @param ack Cumulative acknowledgment
+     * @param blocks SACK blocks as received
+     * @param count Number of blocks
+     * @param result What the ACK delivered and found lost
+     */
+    void orion_tcp_sb_on_ack(orion_tcp_scoreboard_t *sb, uint32_t ack, const orion_tcp_sack_block_t *blocks,
+                             uint8_t count, uint64_t now_ns, orion_tcp_ack_result_t *result);
+
+    /**
+     * @brief Reordering timer fired: mark the segments whose window has passed
+     * @return Segments newly marked lost
+     */
+    uint32_t orion_tcp_sb_on_reo_timeout(orion_tcp_scoreboard_t *sb, uint64_t now_ns);
+
+    /**
+     * @brief RTO fired: every segment not SACKed is lost
+     * @return Segments newly marked lost
+     */
+    uint32_t orion_tcp_sb_on_rto(orion_tcp_scoreboard_t *sb);
+
+    /**
+     * @brief Segments estimated in the network
+     */
+    uint32_t orion_tcp_sb_in_flight(const orion_tcp_scoreboard_t *sb);
+
+    /**
+     * @brief Arm the probe timer after a transmission
+     * @param srtt_ns Smoothed RTT, 0 if unknown
+     * @param rto_ns Current RTO, which the probe never outlasts
+     */
+    void orion_tcp_tlp_arm(orion_tcp_scoreboard_t *sb, uint64_t srtt_ns, uint64_t rto_ns, uint64_t now_ns);
+
+    /**
+     * @brief Probe timer fired
+     * @param has_new_data Whether unsent data fits in the peer's window
+     * @param snd_nxt Sequence number following the data sent so far
+     * @return The segment to retransmit as probe, or NULL to send one new segment instead
+     */
+    orion_tcp_txseg_t *orion_tcp_tlp_fire(orion_tcp_scoreboard_t *sb, bool has_new_data, uint32_t snd_nxt);
+
+    /**
+     * @brief Merge a block of out-of-order data into the blocks a receiver reports
+     */
+    void orion_tcp_rcv_sack_add(orion_tcp_rcv_sack_t *rs, uint32_t start, uint32_t end);
+
+    /**
+     * @brief Forget blocks covered by the cumulative acknowledgment
+     */
+    void orion_tcp_rcv_sack_advance(orion_tcp_rcv_sack_t *rs, uint32_t rcv_nxt);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_TCP_RECOVERY_H
@@ -0,0 +1,680 @@
+/*
+ * Orion Operating System - TLS Cryptographic Primitives
+ *
+ * Portable implementations of the primitives used by the TLS offload
+ * engine. Everything touching secret data (ChaCha20, Poly1305, X25519)
+ * runs in constant time; SHA-256 only ever sees transcripts and keys as
+ * input data, never as control flow.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "tls_crypto.h"
+#include <orion/string.h>
+#include <string.h>
+
+static inline uint32_t load32_le(const uint8_t *p)
+{
+    return (uint32_t)p[0] | ((uint32_t)p[1] << 8) | ((uint32_t)p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+static inline void store32_le(uint8_t *p, uint32_t v)
+{
+    p[0] = (uint8_t)v;
+    p[1] = (uint8_t)(v >> 8);
+    p[2] = (uint8_t)(v >> 16);
+    p[3] = (uint8_t)(v >> 24);
+}
+
+static inline uint32_t load32_be(const uint8_t *p)
+{
+    return ((uint32_t)p[0] << 24) | ((uint32_t)p[1] << 16) | ((uint32_t)p[2] << 8) | (uint32_t)p[3];
+}
+
+static inline void store32_be(uint8_t *p, uint32_t v)
+{
+    p[0] = (uint8_t)(v >> 24);
+    p[1] = (uint8_t)(v >> 16);
+    p[2] = (uint8_t)(v >> 8);
+    p[3] = (uint8_t)v;
+}
+
+static inline uint32_t rotl32(uint32_t v, int n)
+{
+    return (v << n) | (v >> (32 - n));
+}
+
+static inline uint32_t rotr32(uint32_t v, int n)
+{
+    return (v >> n) | (v << (32 - n));
+}
+
+int orion_crypto_compare(const void *a, const void *b, size_t len)
+{
+    const volatile uint8_t *x = a;
+    const volatile uint8_t *y = b;
+    uint8_t diff = 0;
+
+    for (size_t i = 0; i < len; i++) {
+        diff |= x[i] ^ y[i];
+    }
+    return diff != 0 ? -1 : 0;
+}
+
+void orion_crypto_wipe(void *buffer, size_t len)
+{
+    volatile uint8_t *p = buffer;
+    while (len--) {
+        *p++ = 0;
+    }
+}
+
+/* ============================================================================
+ * SHA-256 (FIPS 180-4)
+ * ============================================================================ */
+
+static const uint32_t sha256_k[64] = {
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+};
+
+static void sha256_compress(orion_sha256_ctx_t *ctx, const uint8_t *block)
+{
+    uint32_t w[64];
+    for (int i = 0; i < 16; i++) {
+        w[i] = load32_be(block + i * 4);
+    }
+    for (int i = 16; i < 64; i++) {
+        uint32_t s0 = rotr32(w[i - 15], 7) ^ rotr32(w[i - 15], 18) ^ (w[i - 15] >> 3);
+        uint32_t s1 = rotr32(w[i - 2], 17) ^ rotr32(w[i - 2], 19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+
+    uint32_t a = ctx->state[0], b = ctx->state[1], c = ctx->state[2], d = ctx->state[3];
+    uint32_t e = ctx->state[4], f = ctx->state[5], g = ctx->state[6], h = ctx->state[7];
+
+    for (int i = 0; i < 64; i++) {
+        uint32_t s1 = rotr32(e, 6) ^ rotr32(e, 11) ^ rotr32(e, 25);
+        uint32_t ch = (e & f) ^ (~e & g);
+        uint32_t t1 = h + s1 + ch + sha256_k[i] + w[i];
+        uint32_t s0 = rotr32(a, 2) ^ rotr32(a, 13) ^ rotr32(a, 22);
+        uint32_t maj = (a & b) ^ (a & c) ^ (b & c);
+        uint32_t t2 = s0 + maj;
+
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+
+    ctx->state[0] += a;
+    ctx->state[1] += b;
+    ctx->state[2] += c;
+    ctx->state[3] += d;
+    ctx->state[4] += e;
+    ctx->state[5] += f;
+    ctx->state[6] += g;
+    ctx->state[7] += h;
+}
+
+void orion_sha256_init(orion_sha256_ctx_t *ctx)
+{
+    static const uint32_t initial[8] = {
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    };
+    memcpy(ctx->state, initial, sizeof(initial));
+    ctx->block_len = 0;
+    ctx->length = 0;
+}
+
+void orion_sha256_update(orion_sha256_ctx_t *ctx, const void *data, size_t len)
+{
+    const uint8_t *p = data;
+    ctx->length += len;
+
+    while (len > 0) {
+        size_t chunk = ORION_SHA256_BLOCK_SIZE - ctx->block_len;
+        if (chunk > len) {
+            chunk = len;
+        }
+        memcpy(ctx->block + ctx->block_len, p, chunk);
+        ctx->block_len += chunk;
+        p += chunk;
+        len -= chunk;
+
+        if (ctx->block_len == ORION_SHA256_BLOCK_SIZE) {
+            sha256_compress(ctx, ctx->block);
+            ctx->block_len = 0;
+        }
+    }
+}
+
+void orion_sha256_final(orion_sha256_ctx_t *ctx, uint8_t digest[ORION_SHA256_DIGEST_SIZE])
+{
+    uint64_t bit_length = ctx->length * 8;
+
+    ctx->block[ctx->block_len++] = 0x80;
+    if (ctx->block_len > ORION_SHA256_BLOCK_SIZE - 8) {
+        memset(ctx->block + ctx->block_len, 0, ORION_SHA256_BLOCK_SIZE - ctx->block_len);
+        sha256_compress(ctx, ctx->block);
+        ctx->block_len = 0;
+    }
+    memset(ctx->block + ctx->block_len, 0, ORION_SHA256_BLOCK_SIZE - 8 - ctx->block_len);
+    store32_be(ctx->block + 56, (uint32_t)(bit_length >> 32));
+    store32_be(ctx->block + 60, (uint32_t)bit_length);
+    sha256_compress(ctx, ctx->block);
+
+    for (int i = 0; i < 8; i++) {
+        store32_be(digest + i * 4, ctx->state[i]);
+    }
+    orion_crypto_wipe(ctx, sizeof(*ctx));
+}
+
+void orion_hmac_sha256_init(orion_hmac_sha256_ctx_t *ctx, const uint8_t *key, size_t key_len)
+{
+    uint8_t pad[ORION_SHA256_BLOCK_SIZE];
+    uint8_t key_hash[ORION_SHA256_DIGEST_SIZE];
+
+    if (key_len > ORION_SHA256_BLOCK_SIZE) {
+        orion_sha256_init(&ctx->inner);
+        orion_sha256_update(&ctx->inner, key, key_len);
+        orion_sha256_final(&ctx->inner, key_hash);
+        key = key_hash;
+        key_len = sizeof(key_hash);
+    }
+
+    memset(pad, 0x36, sizeof(pad));
+    for (size_t i = 0; i < key_len; i++) {
+        pad[i] ^= key[i];
+    }
+    orion_sha256_init(&ctx->inner);
+    orion_sha256_update(&ctx->inner, pad, sizeof(pad));
+
+    memset(pad, 0x5c, sizeof(pad));
+    for (size_t i = 0; i < key_len; i++) {
+        pad[i] ^= key[i];
+    }
+    orion_sha256_init(&ctx->outer);
+    orion_sha256_update(&ctx->outer, pad, sizeof(pad));
+
+    orion_crypto_wipe(pad, sizeof(pad));
+    orion_crypto_wipe(key_hash, sizeof(key_hash));
+}
+
+void orion_hmac_sha256_update(orion_hmac_sha256_ctx_t *ctx, const void *data, size_t len)
+{
+    orion_sha256_update(&ctx->inner, data, len);
+}
+
+void orion_hmac_sha256_final(orion_hmac_sha256_ctx_t *ctx, uint8_t mac[ORION_SHA256_DIGEST_SIZE])
+{
+    uint8_t inner[ORION_SHA256_DIGEST_SIZE];
+    orion_sha256_final(&ctx->inner, inner);
+    orion_sha256_update(&ctx->outer, inner, sizeof(inner));
+    orion_sha256_final(&ctx->outer, mac);
+}
+
+void orion_hkdf_extract(const uint8_t *salt, size_t salt_len, const uint8_t *ikm, size_t ikm_len,
+                        uint8_t prk[ORION_SHA256_DIGEST_SIZE])
+{
+    orion_hmac_sha256_ctx_t hmac;
+    orion_hmac_sha256_init(&hmac, salt, salt_len);
+    orion_hmac_sha256_update(&hmac, ikm, ikm_len);
+    orion_hmac_sha256_final(&hmac, prk);
+}
+
+int orion_hkdf_expand(const uint8_t prk[ORION_SHA256_DIGEST_SIZE], const uint8_t *info, size_t info_len,
+                      uint8_t *out, size_t out_len)
+{
+    uint8_t block[ORION_SHA256_DIGEST_SIZE];
+    size_t block_len = 0;
+    uint8_t counter = 1;
+
+    if (out_len > 255 * ORION_SHA256_DIGEST_SIZE) {
+        return -1;
+    }
+
+    while (out_len > 0) {
+        orion_hmac_sha256_ctx_t hmac;
+        orion_hmac_sha256_init(&hmac, prk, ORION_SHA256_DIGEST_SIZE);
+        orion_hmac_sha256_update(&hmac, block, block_len);
+        orion_hmac_sha256_update(&hmac, info, info_len);
+        orion_hmac_sha256_update(&hmac, &counter, 1);
+        orion_hmac_sha256_final(&hmac, block);
+        block_len = sizeof(block);
+
+        size_t chunk = out_len < block_len ? out_len : block_len;
+        memcpy(out, block, chunk);
+        out += chunk;
+        out_len -= chunk;
+        counter++;
+    }
+
+    orion_crypto_wipe(block, sizeof(block));
+    return 0;
+}
+
+/* ============================================================================
+ * ChaCha20 and Poly1305 (RFC 8439)
+ * ============================================================================ */
+
+#define QUARTER_ROUND(a, b, c, d)                  \
+    do {                                           \
+        a += b; d ^= a; d = rotl32(d, 16);         \
+        c += d; b ^= c; b = rotl32(b, 12);         \
+        a += b; d ^= a; d = rotl32(d, 8);          \
+        c += d; b ^= c; b = rotl32(b, 7);          \
+    } while (0)
+
+static void chacha20_block(const uint8_t key[32], uint32_t counter, const uint8_t nonce[12], uint8_t out[64])
+{
+    uint32_t input[16];
+    uint32_t x[16];
+
+    input[0] = 0x61707865;
+    input[1] = 0x3320646e;
+    input[2] = 0x79622d32;
+    input[3] = 0x6b206574;
+    for (int i = 0; i < 8; i++) {
+        input[4 + i] = load32_le(key + i * 4);
+    }
+    input[12] = counter;
+    input[13] = load32_le(nonce);
+    input[14] = load32_le(nonce + 4);
+    input[15] = load32_le(nonce + 8);
+
+    memcpy(x, input, sizeof(x));
+    for (int i = 0; i < 10; i++) {
+        QUARTER_ROUND(x[0], x[4], x[8], x[12]);
+        QUARTER_ROUND(x[1], x[5], x[9], x[13]);
+        QUARTER_ROUND(x[2], x[6], x[10], x[14]);
+        QUARTER_ROUND(x[3], x[7], x[11], x[15]);
+        QUARTER_ROUND(x[0], x[5], x[10], x[15]);
+        QUARTER_ROUND(x[1], x[6], x[11], x[12]);
+        QUARTER_ROUND(x[2], x[7], x[8], x[13]);
+        QUARTER_ROUND(x[3], x[4], x[9], x[14]);
+    }
+    for (int i = 0; i < 16; i++) {
+        store32_le(out + i * 4, x[i] + input[i]);
+    }
+
+    orion_crypto_wipe(input, sizeof(input));
+    orion_crypto_wipe(x, sizeof(x));
+}
+
+static void chacha20_xor(const uint8_t key[32], uint32_t counter, const uint8_t nonce[12],
+                         const uint8_t *in, size_t len, uint8_t *out)
+{
+    uint8_t stream[64];
+
+    while (len > 0) {
+        size_t chunk = len < sizeof(stream) ? len : sizeof(stream);
+        chacha20_block(key, counter++, nonce, stream);
+        for (size_t i = 0; i < chunk; i++) {
+            out[i] = in[i] ^ stream[i];
+        }
+        in += chunk;
+        out += chunk;
+        len -= chunk;
+    }
+    orion_crypto_wipe(stream, sizeof(stream));
+}
+
+// Poly1305 state in radix 2^26
+typedef struct {
+    uint32_t r[5];
+    uint32_t h[5];
+    uint32_t pad[4];
+} poly1305_t;
+
+static void poly1305_init(poly1305_t *st, const uint8_t key[32])
+{
+    st->r[0] = load32_le(key + 0) & 0x3ffffff;
+    st->r[1] = (load32_le(key + 3) >> 2) & 0x3ffff03;
+    st->r[2] = (load32_le(key + 6) >> 4) & 0x3ffc0ff;
+    st->r[3] = (load32_le(key + 9) >> 6) & 0x3f03fff;
+    st->r[4] = (load32_le(key + 12) >> 8) & 0x00fffff;
+    memset(st->h, 0, sizeof(st->h));
+    for (int i = 0; i < 4; i++) {
+        st->pad[i] = load32_le(key + 16 + i * 4);
+    }
+}
+
+static void poly1305_block(poly1305_t *st, const uint8_t m[16])
+{
+    const uint32_t r0 = st->r[0], r1 = st->r[1], r2 = st->r[2], r3 = st->r[3], r4 = st->r[4];
+    const uint32_t s1 = r1 * 5, s2 = r2 * 5, s3 = r3 * 5, s4 = r4 * 5;
+    uint32_t h0 = st->h[0], h1 = st->h[1], h2 = st->h[2], h3 = st->h[3], h4 = st->h[4];
+    uint64_t d0, d1, d2, d3, d4;
+    uint32_t c;
+
+    h0 += load32_le(m + 0) & 0x3ffffff;
+    h1 += (load32_le(m + 3) >> 2) & 0x3ffffff;
+    h2 += (load32_le(m + 6) >> 4) & 0x3ffffff;
+    h3 += (load32_le(m + 9) >> 6) & 0x3ffffff;
+    h4 += (load32_le(m + 12) >> 8) | (1 << 24);
+
+    d0 = (uint64_t)h0 * r0 + (uint64_t)h1 * s4 + (uint64_t)h2 * s3 + (uint64_t)h3 * s2 + (uint64_t)h4 * s1;
+    d1 = (uint64_t)h0 * r1 + (uint64_t)h1 * r0 + (uint64_t)h2 * s4 + (uint64_t)h3 * s3 + (uint64_t)h4 * s2;
+    d2 = (uint64_t)h0 * r2 + (uint64_t)h1 * r1 + (uint64_t)h2 * r0 + (uint64_t)h3 * s4 + (uint64_t)h4 * s3;
+    d3 = (uint64_t)h0 * r3 + (uint64_t)h1 * r2 + (uint64_t)h2 * r1 + (uint64_t)h3 * r0 + (uint64_t)h4 * s4;
+    d4 = (uint64_t)h0 * r4 + (uint64_t)h1 * r3 + (uint64_t)h2 * r2 + (uint64_t)h3 * r1 + (uint64_t)h4 * r0;
+
+    c = (uint32_t)(d0 >> 26); h0 = (uint32_t)d0 & 0x3ffffff;
+    d1 += c; c = (uint32_t)(d1 >> 26); h1 = (uint32_t)d1 & 0x3ffffff;
+    d2 += c; c = (uint32_t)(d2 >> 26); h2 = (uint32_t)d2 & 0x3ffffff;
+    d3 += c; c = (uint32_t)(d3 >> 26); h3 = (uint32_t)d3 & 0x3ffffff;
+    d4 += c; c = (uint32_t)(d4 >> 26); h4 = (uint32_t)d4 & 0x3ffffff;
+    h0 += c * 5; c = h0 >> 26; h0 &= 0x3ffffff;
+    h1 += c;
+
+    st->h[0] = h0;
+    st->h[1] = h1;
+    st->h[2] = h2;
+    st->h[3] = h3;
+    st->h[4] = h4;
+}
+
+// Feed data zero-padded to a multiple of 16 bytes, as the AEAD construction does
+static void poly1305_update_padded(poly1305_t *st, const uint8_t *data, size_t len)
+{
+    uint8_t block[16];
+
+    while (len >= 16) {
+        poly1305_block(st, data);
+        data += 16;
+        len -= 16;
+    }
+    if (len > 0) {
+        memset(block, 0, sizeof(block));
+        memcpy(block, data, len);
+        poly1305_block(st, block);
+    }
+}
+
+static void poly1305_finish(poly1305_t *st, uint8_t tag[16])
+{
+    uint32_t h0 = st->h[0], h1 = st->h[1], h2 = st->h[2], h3 = st->h[3], h4 = st->h[4];
+    uint32_t g0, g1, g2, g3, g4, c, mask;
+    uint64_t f;
+
+    // Fully carry h
+    c = h1 >> 26; h1 &= 0x3ffffff;
+    h2 += c; c = h2 >> 26; h2 &= 0x3ffffff;
+    h3 += c; c = h3 >> 26; h3 &= 0x3ffffff;
+    h4 += c; c = h4 >> 26; h4 &= 0x3ffffff;
+    h0 += c * 5; c = h0 >> 26; h0 &= 0x3ffffff;
+    h1 += c;
+
+    // g = h - p, selected when h >= p
+    g0 = h0 + 5; c = g0 >> 26; g0 &= 0x3ffffff;
+    g1 = h1 + c; c = g1 >> 26; g1 &= 0x3ffffff;
+    g2 = h2 + c; c = g2 >> 26; g2 &= 0x3ffffff;
+    g3 = h3 + c; c = g3 >> 26; g3 &= 0x3ffffff;
+    g4 = h4 + c - (1 << 26);
+
+    mask = (g4 >> 31) - 1;
+    g0 &= mask; g1 &= mask; g2 &= mask; g3 &= mask; g4 &= mask;
+    mask = ~mask;
+    h0 = (h0 & mask) | g0;
+    h1 = (h1 & mask) | g1;
+    h2 = (h2 & mask) | g2;
+    h3 = (h3 & mask) | g3;
+    h4 = (h4 & mask) | g4;
+
+    // h = (h + pad) mod 2^128
+    h0 = h0 | (h1 << 26);
+    h1 = (h1 >> 6) | (h2 << 20);
+    h2 = (h2 >> 12) | (h3 << 14);
+    h3 = (h3 >> 18) | (h4 << 8);
+
+    f = (uint64_t)h0 + st->pad[0]; h0 = (uint32_t)f;
+    f = (uint64_t)h1 + st->pad[1] + (f >> 32); h1 = (uint32_t)f;
+    f = (uint64_t)h2 + st->pad[2] + (f >> 32); h2 = (uint32_t)f;
+    f = (uint64_t)h3 + st->pad[3] + (f >> 32); h3 = (uint32_t)f;
+
+    store32_le(tag + 0, h0);
+    store32_le(tag + 4, h1);
+    store32_le(tag + 8, h2);
+    store32_le(tag + 12, h3);
+    orion_crypto_wipe(st, sizeof(*st));
+}
+
+static void aead_tag(const uint8_t key[32], const uint8_t nonce[12], const uint8_t *aad, size_t aad_len,
+                     const uint8_t *ciphertext, size_t len, uint8_t tag[16])
+{
+    uint8_t block0[64];
+    uint8_t lengths[16];
+    poly1305_t st;
+
+    chacha20_block(key, 0, nonce, block0);
+    poly1305_init(&st, block0);
+    orion_crypto_wipe(block0, sizeof(block0));
+
+    poly1305_update_padded(&st, aad, aad_len);
+    poly1305_update_padded(&st, ciphertext, len);
+    store32_le(lengths + 0, (uint32_t)aad_len);
+    store32_le(lengths + 4, (uint32_t)((uint64_t)aad_len >> 32));
+    store32_le(lengths + 8, (uint32_t)len);
+    store32_le(lengths + 12, (uint32_t)((uint64_t)len >> 32));
+    poly1305_block(&st, lengths);
+    poly1305_finish(&st, tag);
+}
+
+void orion_chacha20_poly1305_seal(const uint8_t key[ORION_CHACHA20_KEY_SIZE],
+                                  const uint8_t nonce[ORION_CHACHA20_NONCE_SIZE],
+                                  const uint8_t *aad, size_t aad_len,
+                                  const uint8_t *plaintext, size_t len, uint8_t *out)
+{
+    chacha20_xor(key, 1, nonce, plaintext, len, out);
+    aead_tag(key, nonce, aad, aad_len, out, len, out + len);
+}
+
+int orion_chacha20_poly1305_open(const uint8_t key[ORION_CHACHA20_KEY_SIZE],
+                                 const uint8_t nonce[ORION_CHACHA20_NONCE_SIZE],
+                                 const uint8_t *aad, size_t aad_len,
+                                 const uint8_t *ciphertext, size_t len, uint8_t *out)
+{
+    uint8_t tag[ORION_POLY1305_TAG_SIZE];
+
+    if (len < ORION_POLY1305_TAG_SIZE) {
+        return -1;
+    }
+    len -= ORION_POLY1305_TAG_SIZE;
+
+    aead_tag(key, nonce, aad, aad_len, ciphertext, len, tag);
+    if (orion_crypto_compare(tag, ciphertext + len, sizeof(tag)) != 0) {
+        orion_crypto_wipe(out, len);
+        return -1;
+    }
+    chacha20_xor(key, 1, nonce, ciphertext, len, out);
+    return 0;
+}
+
+/* ============================================================================
+ * X25519 (RFC 7748), field elements as 16 limbs of 16 bits
+ * ============================================================================ */
+
+typedef int64_t fe25519[16];
+
+static void fe_carry(fe25519 o)
+{
+    for (int i = 0; i < 16; i++) {
+        o[i] += (int64_t)1 << 16;
+        int64_t c = o[i] >> 16;
+        if (i < 15) {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c * ((int64_t)1 << 16);
+    }
+}
+
+// Swap p and q when b is 1, without branching
+static void fe_swap(fe25519 p, fe25519 q, int64_t b)
+{
+    int64_t mask = ~(b - 1);
+    for (int i = 0; i < 16; i++) {
+        int64_t t = mask & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+static void fe_pack(uint8_t out[32], const fe25519 n)
+{
+    fe25519 m, t;
+
+    memcpy(t, n, sizeof(t));
+    fe_carry(t);
+    fe_carry(t);
+    fe_carry(t);
+    for (int j = 0; j < 2; j++) {
+        m[0] = t[0] - 0xffed;
+        for (int i = 1; i < 15; i++) {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        int64_t b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        fe_swap(t, m, 1 - b);
+    }
+    for (int i = 0; i < 16; i++) {
+        out[2 * i] = (uint8_t)(t[i] & 0xff);
+        out[2 * i + 1] = (uint8_t)(t[i] >> 8);
+    }
+}
+
+static void fe_unpack(fe25519 o, const uint8_t n[32])
+{
+    for (int i = 0; i < 16; i++) {
+        o[i] = n[2 * i] + ((int64_t)n[2 * i + 1] << 8);
+    }
+    o[15] &= 0x7fff;
+}
+
+static void fe_add(fe25519 o, const fe25519 a, const fe25519 b)
+{
+    for (int i = 0; i < 16; i++) {
+        o[i] = a[i] + b[i];
+    }
+}
+
+static void fe_sub(fe25519 o, const fe25519 a, const fe25519 b)
+{
+    for (int i = 0; i < 16; i++) {
+        o[i] = a[i] - b[i];
+    }
+}
+
+static void fe_mul(fe25519 o, const fe25519 a, const fe25519 b)
+{
+    int64_t t[31] = {0};
+
+    for (int i = 0; i < 16; i++) {
+        for (int j = 0; j < 16; j++) {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for (int i = 0; i < 15; i++) {
+        t[i] += 38 * t[i + 16];
+    }
+    memcpy(o, t, sizeof(fe25519));
+    fe_carry(o);
+    fe_carry(o);
+}
+
+static void fe_invert(fe25519 o, const fe25519 in)
+{
+    fe25519 c;
+
+    // in^(p - 2)
+    memcpy(c, in, sizeof(c));
+    for (int a = 253; a >= 0; a--) {
+        fe_mul(c, c, c);
+        if (a != 2 && a != 4) {
+            fe_mul(c, c, in);
+        }
+    }
+    memcpy(o, c, sizeof(c));
+}
+
+int orion_x25519(uint8_t out[ORION_X25519_KEY_SIZE], const uint8_t scalar[ORION_X25519_KEY_SIZE],
+                 const uint8_t *point)
+{
+    static const uint8_t base_point[32] = {9};
+    static const fe25519 a24 = {0xdb41, 1};
+    uint8_t z[32];
+    fe25519 x, a, b, c, d, e, f;
+    uint8_t check = 0;
+
+    memcpy(z, scalar, sizeof(z));
+    z[31] = (z[31] & 127) | 64;
+    z[0] &= 248;
+    fe_unpack(x, point ? point : base_point);
+
+    memcpy(b, x, sizeof(b));
+    memset(a, 0, sizeof(a));
+    memset(c, 0, sizeof(c));
+    memset(d, 0, sizeof(d));
+    a[0] = 1;
+    d[0] = 1;
+
+    // Montgomery ladder
+    for (int i = 254; i >= 0; i--) {
+        int64_t bit = (z[i >> 3] >> (i & 7)) & 1;
+        fe_swap(a, b, bit);
+        fe_swap(c, d, bit);
+        fe_add(e, a, c);
+        fe_sub(a, a, c);
+        fe_add(c, b, d);
+        fe_sub(b, b, d);
+        fe_mul(d, e, e);
+        fe_mul(f, a, a);
+        fe_mul(a, c, a);
+        fe_mul(c, b, e);
+        fe_add(e, a, c);
+        fe_sub(a, a, c);
+        fe_mul(b, a, a);
+        fe_sub(c, d, f);
+        fe_mul(a, c, a24);
+        fe_add(a, a, d);
+        fe_mul(c, c, a);
+        fe_mul(a, d, f);
+        fe_mul(d, b, x);
+        fe_mul(b, e, e);
+        fe_swap(a, b, bit);
+        fe_swap(c, d, bit);
+    }
+
+    fe_invert(c, c);
+    fe_mul(a, a, c);
+    fe_pack(out, a);
+
+    orion_crypto_wipe(z, sizeof(z));
+    orion_crypto_wipe(a, sizeof(a));
+    orion_crypto_wipe(b, sizeof(b));
+    orion_crypto_wipe(c, sizeof(c));
+    orion_crypto_wipe(d, sizeof(d));
+
+    for (int i = 0; i < ORION_X25519_KEY_SIZE; i++) {
+        check |= out[i];
+    }
+    return check == 0 ? -1 : 0;
+}
@@ -0,0 +1,121 @@
+/*
+ * Orion Operating System - TLS Cryptographic Primitives
+ *
+ * Primitives behind the TLS offload engine: SHA-256, HMAC and HKDF for the
+ * TLS 1.3 key schedule, ChaCha20-Poly1305 for record protection and X25519
+ * for the key exchange.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_TLS_CRYPTO_H
+#define ORION_TLS_CRYPTO_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define ORION_SHA256_DIGEST_SIZE 32
+#define ORION_SHA256_BLOCK_SIZE 64
+#define ORION_CHACHA20_KEY_SIZE 32
+#define ORION_CHACHA20_NONCE_SIZE 12
+#define ORION_POLY1305_TAG_SIZE 16
+#define ORION_X25519_KEY_SIZE 32
+
+    /* ============================================================================
+     * SHA-256 / HMAC / HKDF
+     * ============================================================================ */
+
+    typedef struct
+    {
+        uint32_t state[8];
+        uint8_t block[ORION_SHA256_BLOCK_SIZE];
+        size_t block_len;
+        uint64_t length;
+    } orion_sha256_ctx_t;
+
+    typedef struct
+    {
+        orion_sha256_ctx_t inner;
+        orion_sha256_ctx_t outer;
+    } orion_hmac_sha256_ctx_t;
+
+    void orion_sha256_init(orion_sha256_ctx_t *ctx);
+    void orion_sha256_update(orion_sha256_ctx_t *ctx, const void *data, size_t len);
+    void orion_sha256_final(orion_sha256_ctx_t *ctx, uint8_t digest[ORION_SHA256_DIGEST_SIZE]);
+
+    void orion_hmac_sha256_init(orion_hmac_sha256_ctx_t *ctx, const uint8_t *key, size_t key_len);
+    void orion_hmac_sha256_update(orion_hmac_sha256_ctx_t *ctx, const void *data, size_t len);
+    void orion_hmac_sha256_final(orion_hmac_sha256_ctx_t *ctx, uint8_t mac[ORION_SHA256_DIGEST_SIZE]);
+
+    /**
+     * @brief HKDF-Extract (RFC 5869) with SHA-256
+     */
+    void orion_hkdf_extract(const uint8_t *salt, size_t salt_len, const uint8_t *ikm, size_t ikm_len,
+                            uint8_t prk[ORION_SHA256_DIGEST_SIZE]);
+
+    /**
+     * @brief HKDF-Expand (RFC 5869) with SHA-256
+     * @return 0 on success, -1 if out_len exceeds 255 blocks
+     */
+    int orion_hkdf_expand(const uint8_t prk[ORION_SHA256_DIGEST_SIZE], const uint8_t *info, size_t info_len,
+                          uint8_t *out, size_t out_len);
+
+    /* ============================================================================
+     * ChaCha20-Poly1305 AEAD (RFC 8439)
+     * ============================================================================ */
+
+    /**
+     * @brief Encrypt and authenticate; out receives len + 16 bytes
+     */
+    void orion_chacha20_poly1305_seal(const uint8_t key[ORION_CHACHA20_KEY_SIZE],
+                                      const uint8_t nonce[ORION_CHACHA20_NONCE_SIZE],
+                                      const uint8_t *aad, size_t aad_len,
+                                      const uint8_t *plaintext, size_t len, uint8_t *out);
+
+    /**
+     * @brief Verify and decrypt; len includes the 16-byte tag
+     * @return 0 on success, -1 if authentication fails (out is then zeroed)
+     */
+    int orion_chacha20_poly1305_open(const uint8_t key[ORION_CHACHA20_KEY_SIZE],
+                                     const uint8_t nonce[ORION_CHACHA20_NONCE_SIZE],
+                                     const uint8_t *aad, size_t aad_len,
+                                     const uint8_t *ciphertext, size_t len, uint8_t *out);
+
+    /* ============================================================================
+     * X25519 (RFC 7748)
+     * ============================================================================ */
+
+    /**
+     * @brief Scalar multiplication on Curve25519
+     * @param out Shared secret or public key
+     * @param scalar Private key
+     * @param point Peer public key, or NULL for the base point
+     * @return 0 on success, -1 if the result is all zeros (low-order point)
+     */
+    int orion_x25519(uint8_t out[ORION_X25519_KEY_SIZE], const uint8_t scalar[ORION_X25519_KEY_SIZE],
+                     const uint8_t *point);
+
+    /**
+     * @brief Compare two buffers in constant time
+     * @return 0 when equal
+     */
+    int orion_crypto_compare(const void *a, const void *b, size_t len);
+
+    /**
+     * @brief Zero a buffer holding secrets
+     */
+    void orion_crypto_wipe(void *buffer, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_TLS_CRYPTO_H
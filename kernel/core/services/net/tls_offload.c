@@ -0,0 +1,1820 @@
+/*
+ * Orion Operating System - TLS Termination Offload
+ *
+ * TLS 1.3 (RFC 8446) engine used to terminate TLS on behalf of listening
+ * sockets, and to run the client side of connections opened by local
+ * processes. The engine does no I/O itself: the TCP layer feeds it the
+ * bytes received from the peer, drains the protected bytes it produces and
+ * exchanges plaintext with the application.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include "tls_offload.h"
+#include "tls_crypto.h"
+#include "keyring_client.h"
+#include <orion/klog.h>
+#include <orion/mm.h>
+#include <orion/string.h>
+#include <orion/spinlock.h>
+#include <orion/wallclock.h>
+#include <string.h>
+
+extern uint64_t security_get_random(void);
+
+/* ============================================================================
+ * Protocol Constants
+ * ============================================================================ */
+
+#define TLS_RECORD_HEADER 5
+
+// Record content types
+#define TLS_CONTENT_CHANGE_CIPHER_SPEC 20
+#define TLS_CONTENT_ALERT 21
+#define TLS_CONTENT_HANDSHAKE 22
+#define TLS_CONTENT_APPLICATION_DATA 23
+
+// Handshake message types
+#define TLS_HS_CLIENT_HELLO 1
+#define TLS_HS_SERVER_HELLO 2
+#define TLS_HS_NEW_SESSION_TICKET 4
+#define TLS_HS_ENCRYPTED_EXTENSIONS 8
+#define TLS_HS_CERTIFICATE 11
+#define TLS_HS_CERTIFICATE_VERIFY 15
+#define TLS_HS_FINISHED 20
+#define TLS_HS_KEY_UPDATE 24
+
+// Extensions
+#define TLS_EXT_SERVER_NAME 0
+#define TLS_EXT_SUPPORTED_GROUPS 10
+#define TLS_EXT_SIGNATURE_ALGORITHMS 13
+#define TLS_EXT_SUPPORTED_VERSIONS 43
+#define TLS_EXT_KEY_SHARE 51
+
+#define TLS_VERSION_12 0x0303
+#define TLS_VERSION_13 0x0304
+#define TLS_CIPHER_CHACHA20_POLY1305_SHA256 0x1303
+#define TLS_GROUP_X25519 0x001d
+#define TLS_SIGNATURE_ED25519 0x0807
+
+#define TLS_ALERT_LEVEL_WARNING 1
+#define TLS_ALERT_LEVEL_FATAL 2
+
+#define TLS_HASH_SIZE ORION_SHA256_DIGEST_SIZE
+#define TLS_MAX_HANDSHAKE 16384
+#define TLS_OUTPUT_CAPACITY (3 * ORION_TLS_MAX_RECORD)
+#define TLS_MAX_CHAIN 4 // Certificates a client accepts from a server
+
+// DER tags of the certificate fields the client looks at
+#define DER_BOOLEAN 0x01
+#define DER_INTEGER 0x02
+#define DER_BIT_STRING 0x03
+#define DER_OCTET_STRING 0x04
+#define DER_OID 0x06
+#define DER_UTC_TIME 0x17
+#define DER_GENERALIZED_TIME 0x18
+#define DER_SEQUENCE 0x30
+#define DER_VERSION 0xa0    // [0] EXPLICIT in TBSCertificate
+#define DER_EXTENSIONS 0xa3 // [3] EXPLICIT in TBSCertificate
+#define DER_SAN_DNS_NAME 0x82
+#define DER_SAN_IP_ADDRESS 0x87
+
+// Context string of the server CertificateVerify signature
+static const char tls_server_verify_context[] = "TLS 1.3, server CertificateVerify";
+
+// ServerHello.random of a HelloRetryRequest (RFC 8446 section 4.1.3)
+static const uint8_t tls_hello_retry_random[32] = {
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+};
+
+static const uint8_t der_oid_ed25519[] = {0x2b, 0x65, 0x70};
+static const uint8_t der_oid_subject_alt_name[] = {0x55, 0x1d, 0x11};
+static const uint8_t der_oid_basic_constraints[] = {0x55, 0x1d, 0x13};
+
+/* ============================================================================
+ * Session State
+ * ============================================================================ */
+
+typedef struct {
+    uint8_t secret[TLS_HASH_SIZE];
+    uint8_t key[ORION_CHACHA20_KEY_SIZE];
+    uint8_t iv[ORION_CHACHA20_NONCE_SIZE];
+    uint64_t sequence;
+    bool active;
+} tls_traffic_t;
+
+struct orion_tls_session {
+    orion_tls_listener_t *listener; // NULL on the client side
+    orion_tls_state_t state;
+
+    // Key schedule
+    orion_sha256_ctx_t transcript;
+    uint8_t client_handshake_secret[TLS_HASH_SIZE];
+    uint8_t client_application_secret[TLS_HASH_SIZE];
+    uint8_t server_handshake_secret[TLS_HASH_SIZE]; // Client side
+    uint8_t master_secret[TLS_HASH_SIZE];           // Client side, until the server Finished
+    uint8_t exporter_secret[TLS_HASH_SIZE];         // exporter_master_secret, for orion_tls_session_export
+    tls_traffic_t read;
+    tls_traffic_t write;
+
+    // Partially received record
+    uint8_t record[ORION_TLS_MAX_RECORD];
+    size_t record_len;
+
+    // Handshake message reassembly
+    uint8_t handshake[TLS_MAX_HANDSHAKE + 4];
+    size_t handshake_len;
+
+    // Decrypted application data, also scratch space while handshaking
+    uint8_t plaintext[ORION_TLS_MAX_RECORD];
+    size_t plaintext_off;
+    size_t plaintext_len;
+
+    // Protected bytes waiting to be transmitted
+    uint8_t output[TLS_OUTPUT_CAPACITY];
+    size_t output_len;
+
+    // Client side: server identity and the key proving it
+    uint8_t private_key[ORION_X25519_KEY_SIZE];
+    uint8_t session_id[32];
+    uint8_t anchor_key[ORION_KEYRING_PUBLIC_KEY_SIZE];
+    uint8_t server_key[ORION_KEYRING_PUBLIC_KEY_SIZE];
+    char server_name[ORION_TLS_MAX_SERVER_NAME + 1];
+};
+
+// Fields of a parsed certificate, pointing into its DER encoding
+typedef struct {
+    const uint8_t *tbs; // TBSCertificate, the signed part
+    size_t tbs_len;
+    const uint8_t *public_key; // Ed25519 subject key
+    const uint8_t *signature;  // Ed25519 signature of the issuer
+    int64_t not_before;        // Validity, seconds since the epoch
+    int64_t not_after;
+    const uint8_t *extensions; // Contents of the extension list, if any
+    size_t extensions_len;
+} tls_certificate_t;
+
+static spinlock_t tls_listener_lock = SPINLOCK_INITIALIZER;
+
+/* ============================================================================
+ * Helpers
+ * ============================================================================ */
+
+typedef struct {
+    const uint8_t *data;
+    size_t len;
+    size_t off;
+    bool error;
+} tls_reader_t;
+
+static void reader_init(tls_reader_t *r, const uint8_t *data, size_t len)
+{
+    r->data = data;
+    r->len = len;
+    r->off = 0;
+    r->error = false;
+}
+
+static const uint8_t *reader_bytes(tls_reader_t *r, size_t n)
+{
+    if (r->error || r->len - r->off < n) {
+        r->error = true;
+        return NULL;
+    }
+    const uint8_t *p = r->data + r->off;
+    r->off += n;
+    return p;
+}
+
+static uint32_t reader_uint(tls_reader_t *r, size_t n)
+{
+    const uint8_t *p = reader_bytes(r, n);
+    uint32_t value = 0;
+    for (size_t i = 0; p && i < n; i++) {
+        value = (value << 8) | p[i];
+    }
+    return value;
+}
+
+// Carve a length-prefixed vector out of r
+static void reader_vector(tls_reader_t *r, size_t length_size, tls_reader_t *vector)
+{
+    size_t len = reader_uint(r, length_size);
+    const uint8_t *p = reader_bytes(r, len);
+    reader_init(vector, p, p ? len : 0);
+    vector->error = r->error;
+}
+
+static void put_u16(uint8_t *p, uint32_t v)
+{
+    p[0] = (uint8_t)(v >> 8);
+    p[1] = (uint8_t)v;
+}
+
+static void put_u24(uint8_t *p, uint32_t v)
+{
+    p[0] = (uint8_t)(v >> 16);
+    p[1] = (uint8_t)(v >> 8);
+    p[2] = (uint8_t)v;
+}
+
+static void tls_random(uint8_t *out, size_t len)
+{
+    while (len > 0) {
+        uint64_t word = security_get_random();
+        size_t chunk = len < sizeof(word) ? len : sizeof(word);
+        memcpy(out, &word, chunk);
+        out += chunk;
+        len -= chunk;
+    }
+}
+
+/*
+ * Total size of the DER SEQUENCE starting at p, 0 if it is not one or
+ * does not fit in avail bytes
+ */
+static size_t der_sequence_size(const uint8_t *p, size_t avail)
+{
+    size_t header = 2;
+    size_t len;
+
+    if (avail < 2 || p[0] != 0x30) {
+        return 0;
+    }
+    if (p[1] < 0x80) {
+        len = p[1];
+    } else {
+        size_t count = p[1] & 0x7f;
+        if (count == 0 || count > 3 || avail < 2 + count) {
+            return 0;
+        }
+        len = 0;
+        for (size_t i = 0; i < count; i++) {
+            len = (len << 8) | p[2 + i];
+        }
+        header += count;
+    }
+    return header + len <= avail ? header + len : 0;
+}
+
+/*
+ * Carve the DER element at r out if its tag is `tag`; contents go to
+ * out. Returns the start of the element, NULL (and r->error) otherwise
+ */
+static const uint8_t *der_element(tls_reader_t *r, uint8_t tag, tls_reader_t *out)
+{
+    size_t start = r->off;
+    size_t len;
+
+    if (r->error || r->off >= r->len || r->data[r->off] != tag) {
+        r->error = true;
+    }
+    reader_bytes(r, 1);
+    len = reader_uint(r, 1);
+    if (len >= 0x80) {
+        size_t count = len & 0x7f;
+        if (count == 0 || count > 3) {
+            r->error = true;
+        }
+        len = reader_uint(r, count);
+    }
+    const uint8_t *p = reader_bytes(r, len);
+    reader_init(out, p, p ? len : 0);
+    out->error = r->error;
+    return r->error ? NULL : r->data + start;
+}
+
+static bool der_peek(const tls_reader_t *r, uint8_t tag)
+{
+    return !r->error && r->off < r->len && r->data[r->off] == tag;
+}
+
+// AlgorithmIdentifier naming Ed25519, which has no parameters
+static bool der_is_ed25519(tls_reader_t *algorithm)
+{
+    tls_reader_t oid;
+    der_element(algorithm, DER_OID, &oid);
+    return !algorithm->error && algorithm->off == algorithm->len && oid.len == sizeof(der_oid_ed25519) &&
+           memcmp(oid.data, der_oid_ed25519, sizeof(der_oid_ed25519)) == 0;
+}
+
+static bool der_digits(const uint8_t *p, size_t n, int64_t *value)
+{
+    *value = 0;
+    for (size_t i = 0; i < n; i++) {
+        if (p[i] < '0' || p[i] > '9') {
+            return false;
+        }
+        *value = *value * 10 + (p[i] - '0');
+    }
+    return true;
+}
+
+// UTCTime or GeneralizedTime in UTC, as seconds since the epoch
+static bool der_time(tls_reader_t *r, int64_t *seconds)
+{
+    size_t year_digits = der_peek(r, DER_UTC_TIME) ? 2 : 4;
+    int64_t year, month, day, hour, minute, second;
+    tls_reader_t t;
+
+    der_element(r, year_digits == 2 ? DER_UTC_TIME : DER_GENERALIZED_TIME, &t);
+    if (r->error || t.len != year_digits + 11 || t.data[t.len - 1] != 'Z') {
+        return false;
+    }
+    if (!der_digits(t.data, year_digits, &year) ||
+        !der_digits(t.data + year_digits, 2, &month) ||
+        !der_digits(t.data + year_digits + 2, 2, &day) ||
+        !der_digits(t.data + year_digits + 4, 2, &hour) ||
+        !der_digits(t.data + year_digits + 6, 2, &minute) ||
+        !der_digits(t.data + year_digits + 8, 2, &second) ||
+        month < 1 || month > 12 || day < 1 || day > 31) {
+        return false;
+    }
+    if (year_digits == 2) {
+        year += year < 50 ? 2000 : 1900;
+    }
+
+    // Days from the civil date (proleptic Gregorian calendar)
+    int64_t y = year - (month <= 2);
+    int64_t era = y / 400;
+    int64_t year_of_era = y - era * 400;
+    int64_t day_of_year = (153 * (month > 2 ? month - 3 : month + 9) + 2) / 5 + day - 1;
+    int64_t day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    int64_t days = era * 146097 + day_of_era - 719468;
+
+    *seconds = days * 86400 + hour * 3600 + minute * 60 + second;
+    return true;
+}
+
+/* ============================================================================
+ * Certificates (client side)
+ * ============================================================================ */
+
+static int tls_parse_certificate(const uint8_t *data, size_t len, tls_certificate_t *cert)
+{
+    tls_reader_t r, certificate, tbs, field, algorithm, key;
+
+    memset(cert, 0, sizeof(*cert));
+    reader_init(&r, data, len);
+    der_element(&r, DER_SEQUENCE, &certificate);
+    cert->tbs = der_element(&certificate, DER_SEQUENCE, &tbs);
+    cert->tbs_len = cert->tbs ? (size_t)(certificate.data + certificate.off - cert->tbs) : 0;
+    der_element(&certificate, DER_SEQUENCE, &algorithm);
+    if (!der_is_ed25519(&algorithm)) {
+        return -1;
+    }
+    der_element(&certificate, DER_BIT_STRING, &field);
+    if (reader_uint(&field, 1) != 0) {
+        return -1;
+    }
+    cert->signature = reader_bytes(&field, ORION_KEYRING_SIGNATURE_SIZE);
+    if (r.error || r.off != r.len || certificate.off != certificate.len || field.error || field.off != field.len) {
+        return -1;
+    }
+
+    if (der_peek(&tbs, DER_VERSION)) {
+        der_element(&tbs, DER_VERSION, &field);
+    }
+    der_element(&tbs, DER_INTEGER, &field);  // serialNumber
+    der_element(&tbs, DER_SEQUENCE, &field); // signature
+    der_element(&tbs, DER_SEQUENCE, &field); // issuer
+    der_element(&tbs, DER_SEQUENCE, &field); // validity
+    if (!der_time(&field, &cert->not_before) || !der_time(&field, &cert->not_after)) {
+        return -1;
+    }
+    der_element(&tbs, DER_SEQUENCE, &field); // subject
+
+    der_element(&tbs, DER_SEQUENCE, &key);
+    der_element(&key, DER_SEQUENCE, &algorithm);
+    if (!der_is_ed25519(&algorithm)) {
+        return -1;
+    }
+    der_element(&key, DER_BIT_STRING, &field);
+    if (reader_uint(&field, 1) != 0) {
+        return -1;
+    }
+    cert->public_key = reader_bytes(&field, ORION_KEYRING_PUBLIC_KEY_SIZE);
+    if (key.error || field.error || field.off != field.len) {
+        return -1;
+    }
+
+    // Unique identifiers are skipped, extensions kept
+    while (!tbs.error && tbs.off < tbs.len) {
+        if (der_peek(&tbs, DER_EXTENSIONS)) {
+            tls_reader_t list;
+            der_element(&tbs, DER_EXTENSIONS, &field);
+            der_element(&field, DER_SEQUENCE, &list);
+            cert->extensions = list.data;
+            cert->extensions_len = list.len;
+        } else {
+            der_element(&tbs, tbs.data[tbs.off], &field);
+        }
+    }
+    return tbs.error ? -1 : 0;
+}
+
+// Value of the extension `oid`, false if the certificate has none
+static bool tls_certificate_extension(const tls_certificate_t *cert, const uint8_t *oid, size_t oid_len,
+                                      tls_reader_t *value)
+{
+    tls_reader_t list, extension, id;
+
+    reader_init(&list, cert->extensions, cert->extensions_len);
+    while (!list.error && list.off < list.len) {
+        der_element(&list, DER_SEQUENCE, &extension);
+        der_element(&extension, DER_OID, &id);
+        if (der_peek(&extension, DER_BOOLEAN)) {
+            der_element(&extension, DER_BOOLEAN, value); // critical
+        }
+        der_element(&extension, DER_OCTET_STRING, value);
+        if (!extension.error && id.len == oid_len && memcmp(id.data, oid, oid_len) == 0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// basicConstraints with cA set
+static bool tls_certificate_is_ca(const tls_certificate_t *cert)
+{
+    tls_reader_t value, constraints, ca;
+
+    if (!tls_certificate_extension(cert, der_oid_basic_constraints, sizeof(der_oid_basic_constraints), &value)) {
+        return false;
+    }
+    der_element(&value, DER_SEQUENCE, &constraints);
+    if (!der_peek(&constraints, DER_BOOLEAN)) {
+        return false;
+    }
+    der_element(&constraints, DER_BOOLEAN, &ca);
+    return !ca.error && ca.len == 1 && ca.data[0] != 0;
+}
+
+// Dotted IPv4 address, false for host names
+static bool tls_parse_ipv4(const char *name, uint8_t address[4])
+{
+    for (int i = 0; i < 4; i++) {
+        uint32_t value = 0;
+        int digits = 0;
+        while (*name >= '0' && *name <= '9' && digits < 3) {
+            value = value * 10 + (uint32_t)(*name++ - '0');
+            digits++;
+        }
+        if (digits == 0 || value > 255 || *name != (i < 3 ? '.' : '\0')) {
+            return false;
+        }
+        address[i] = (uint8_t)value;
+        name += i < 3;
+    }
+    return true;
+}
+
+static char tls_lower(char c)
+{
+    return c >= 'A' && c <= 'Z' ? (char)(c - 'A' + 'a') : c;
+}
+
+// dNSName against a host name; a leading "*." matches one whole label
+static bool tls_dns_name_matches(const uint8_t *pattern, size_t pattern_len, const char *name)
+{
+    size_t name_len = strlen(name);
+
+    if (pattern_len > 2 && pattern[0] == '*' && pattern[1] == '.') {
+        const char *dot = strchr(name, '.');
+        if (!dot || dot == name) {
+            return false;
+        }
+        pattern++;
+        pattern_len--;
+        name_len -= (size_t)(dot - name);
+        name = dot;
+    }
+    if (pattern_len != name_len) {
+        return false;
+    }
+    for (size_t i = 0; i < name_len; i++) {
+        if (tls_lower((char)pattern[i]) != tls_lower(name[i])) {
+            return false;
+        }
+    }
+    return true;
+}
+
+// The leaf names the host in its subjectAltName
+static bool tls_certificate_names(const tls_certificate_t *cert, const char *name)
+{
+    tls_reader_t value, names, entry;
+    uint8_t address[4];
+    bool ip = tls_parse_ipv4(name, address);
+
+    if (!tls_certificate_extension(cert, der_oid_subject_alt_name, sizeof(der_oid_subject_alt_name), &value)) {
+        return false;
+    }
+    der_element(&value, DER_SEQUENCE, &names);
+    while (!names.error && names.off < names.len) {
+        uint8_t tag = names.data[names.off];
+        der_element(&names, tag, &entry);
+        if (names.error) {
+            break;
+        }
+        if (ip && tag == DER_SAN_IP_ADDRESS && entry.len == 4 && memcmp(entry.data, address, 4) == 0) {
+            return true;
+        }
+        if (!ip && tag == DER_SAN_DNS_NAME && tls_dns_name_matches(entry.data, entry.len, name)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+/*
+ * Check the chain a server sent, leaf first. Returns 0 or the alert to
+ * send
+ */
+static int tls_verify_chain(const orion_tls_session_t *session, const tls_certificate_t *chain, size_t count)
+{
+    int64_t now = (int64_t)(wallclock_realtime_ns() / WALLCLOCK_NS_PER_SEC);
+
+    if (!tls_certificate_names(&chain[0], session->server_name)) {
+        return ORION_TLS_ALERT_BAD_CERTIFICATE;
+    }
+
+    for (size_t i = 0; i < count; i++) {
+        const tls_certificate_t *cert = &chain[i];
+        if (now < cert->not_before || now > cert->not_after) {
+            return ORION_TLS_ALERT_CERTIFICATE_EXPIRED;
+        }
+        // Servers may send the anchor itself, or be the anchor
+        if (memcmp(cert->public_key, session->anchor_key, sizeof(session->anchor_key)) == 0) {
+            return 0;
+        }
+        if (i + 1 == count) {
+            bool signed_by_anchor = orion_keyring_verify(session->anchor_key, cert->tbs, cert->tbs_len,
+                                                         cert->signature) == 0;
+            return signed_by_anchor ? 0 : ORION_TLS_ALERT_UNKNOWN_CA;
+        }
+        if (!tls_certificate_is_ca(&chain[i + 1]) ||
+            orion_keyring_verify(chain[i + 1].public_key, cert->tbs, cert->tbs_len, cert->signature) != 0) {
+            return ORION_TLS_ALERT_BAD_CERTIFICATE;
+        }
+    }
+    return ORION_TLS_ALERT_BAD_CERTIFICATE;
+}
+
+/* ============================================================================
+ * Key Schedule (RFC 8446 section 7)
+ * ============================================================================ */
+
+static void tls_expand_label(const uint8_t secret[TLS_HASH_SIZE], const char *label,
+                             const uint8_t *context, size_t context_len, uint8_t *out, size_t out_len)
+{
+    uint8_t info[2 + 1 + 6 + 32 + 1 + TLS_HASH_SIZE];
+    size_t label_len = strlen(label);
+    size_t n = 0;
+
+    put_u16(info, (uint32_t)out_len);
+    n += 2;
+    info[n++] = (uint8_t)(6 + label_len);
+    memcpy(info + n, "tls13 ", 6);
+    n += 6;
+    memcpy(info + n, label, label_len);
+    n += label_len;
+    info[n++] = (uint8_t)context_len;
+    if (context_len > 0) {
+        memcpy(info + n, context, context_len);
+        n += context_len;
+    }
+
+    orion_hkdf_expand(secret, info, n, out, out_len);
+}
+
+// Derive-Secret(secret, label, "") as used between extraction stages
+static void tls_derive_empty(const uint8_t secret[TLS_HASH_SIZE], uint8_t out[TLS_HASH_SIZE])
+{
+    uint8_t empty_hash[TLS_HASH_SIZE];
+    orion_sha256_ctx_t ctx;
+
+    orion_sha256_init(&ctx);
+    orion_sha256_final(&ctx, empty_hash);
+    tls_expand_label(secret, "derived", empty_hash, sizeof(empty_hash), out, TLS_HASH_SIZE);
+}
+
+static void tls_transcript_hash(const orion_tls_session_t *session, uint8_t out[TLS_HASH_SIZE])
+{
+    orion_sha256_ctx_t copy = session->transcript;
+    orion_sha256_final(&copy, out);
+}
+
+static void tls_set_traffic(tls_traffic_t *traffic, const uint8_t secret[TLS_HASH_SIZE])
+{
+    memcpy(traffic->secret, secret, TLS_HASH_SIZE);
+    tls_expand_label(secret, "key", NULL, 0, traffic->key, sizeof(traffic->key));
+    tls_expand_label(secret, "iv", NULL, 0, traffic->iv, sizeof(traffic->iv));
+    traffic->sequence = 0;
+    traffic->active = true;
+}
+
+static void tls_update_traffic(tls_traffic_t *traffic)
+{
+    uint8_t next[TLS_HASH_SIZE];
+    tls_expand_label(traffic->secret, "traffic upd", NULL, 0, next, sizeof(next));
+    tls_set_traffic(traffic, next);
+    orion_crypto_wipe(next, sizeof(next));
+}
+
+static void tls_nonce(const tls_traffic_t *traffic, uint8_t nonce[ORION_CHACHA20_NONCE_SIZE])
+{
+    memcpy(nonce, traffic->iv, ORION_CHACHA20_NONCE_SIZE);
+    for (int i = 0; i < 8; i++) {
+        nonce[ORION_CHACHA20_NONCE_SIZE - 1 - i] ^= (uint8_t)(traffic->sequence >> (8 * i));
+    }
+}
+
+/* ============================================================================
+ * Record Layer
+ * ============================================================================ */
+
+static int tls_write_record(orion_tls_session_t *session, uint8_t type, const uint8_t *data, size_t len)
+{
+    size_t protected_len = session->write.active ? len + 1 + ORION_POLY1305_TAG_SIZE : len;
+    uint8_t *out = session->output + session->output_len;
+
+    if (len > ORION_TLS_MAX_PLAINTEXT ||
+        TLS_RECORD_HEADER + protected_len > sizeof(session->output) - session->output_len) {
+        return -1;
+    }
+
+    if (!session->write.active) {
+        out[0] = type;
+        put_u16(out + 1, TLS_VERSION_12);
+        put_u16(out + 3, (uint32_t)len);
+        memmove(out + TLS_RECORD_HEADER, data, len);
+    } else {
+        uint8_t nonce[ORION_CHACHA20_NONCE_SIZE];
+
+        // Protected records all look like application data on the wire
+        out[0] = TLS_CONTENT_APPLICATION_DATA;
+        put_u16(out + 1, TLS_VERSION_12);
+        put_u16(out + 3, (uint32_t)protected_len);
+        memmove(out + TLS_RECORD_HEADER, data, len);
+        out[TLS_RECORD_HEADER + len] = type;
+
+        tls_nonce(&session->write, nonce);
+        orion_chacha20_poly1305_seal(session->write.key, nonce, out, TLS_RECORD_HEADER,
+                                     out + TLS_RECORD_HEADER, len + 1, out + TLS_RECORD_HEADER);
+        session->write.sequence++;
+    }
+
+    session->output_len += TLS_RECORD_HEADER + protected_len;
+    return 0;
+}
+
+static void tls_send_alert(orion_tls_session_t *session, uint8_t level, uint8_t description)
+{
+    uint8_t alert[2] = {level, description};
+    tls_write_record(session, TLS_CONTENT_ALERT, alert, sizeof(alert));
+}
+
+static void tls_fail(orion_tls_session_t *session, uint8_t description)
+{
+    if (session->state == ORION_TLS_STATE_FAILED) {
+        return;
+    }
+    if (session->listener && session->state != ORION_TLS_STATE_CONNECTED &&
+        session->state != ORION_TLS_STATE_CLOSED) {
+        session->listener->handshake_failures++;
+    }
+
+    klog_debug(KLOG_CAT_KERNEL, "TLS session failed: alert %u", description);
+    tls_send_alert(session, TLS_ALERT_LEVEL_FATAL, description);
+    session->state = ORION_TLS_STATE_FAILED;
+    session->handshake_len = 0;
+    session->plaintext_off = 0;
+    session->plaintext_len = 0;
+}
+
+// Append a handshake message built in the scratch buffer and send it
+static int tls_send_handshake(orion_tls_session_t *session, size_t len)
+{
+    orion_sha256_update(&session->transcript, session->plaintext, len);
+    return tls_write_record(session, TLS_CONTENT_HANDSHAKE, session->plaintext, len);
+}
+
+/* ============================================================================
+ * Handshake
+ * ============================================================================ */
+
+static int tls_send_server_hello(orion_tls_session_t *session, const uint8_t *session_id,
+                                 size_t session_id_len, const uint8_t public_key[ORION_X25519_KEY_SIZE])
+{
+    uint8_t *m = session->plaintext;
+    size_t n = 4;
+
+    put_u16(m + n, TLS_VERSION_12);
+    n += 2;
+    tls_random(m + n, 32);
+    n += 32;
+    m[n++] = (uint8_t)session_id_len;
+    memcpy(m + n, session_id, session_id_len);
+    n += session_id_len;
+    put_u16(m + n, TLS_CIPHER_CHACHA20_POLY1305_SHA256);
+    n += 2;
+    m[n++] = 0;
+
+    // supported_versions and key_share extensions
+    put_u16(m + n, 6 + 40);
+    n += 2;
+    put_u16(m + n, TLS_EXT_SUPPORTED_VERSIONS);
+    put_u16(m + n + 2, 2);
+    put_u16(m + n + 4, TLS_VERSION_13);
+    n += 6;
+    put_u16(m + n, TLS_EXT_KEY_SHARE);
+    put_u16(m + n + 2, 36);
+    put_u16(m + n + 4, TLS_GROUP_X25519);
+    put_u16(m + n + 6, ORION_X25519_KEY_SIZE);
+    memcpy(m + n + 8, public_key, ORION_X25519_KEY_SIZE);
+    n += 40;
+
+    m[0] = TLS_HS_SERVER_HELLO;
+    put_u24(m + 1, (uint32_t)(n - 4));
+    return tls_send_handshake(session, n);
+}
+
+static int tls_send_certificate(orion_tls_session_t *session)
+{
+    const orion_tls_listener_t *listener = session->listener;
+    uint8_t *m = session->plaintext;
+    size_t n = 8;
+    size_t off = 0;
+
+    while (off < listener->certificate_chain_len) {
+        size_t size = der_sequence_size(listener->certificate_chain + off,
+                                        listener->certificate_chain_len - off);
+        if (size == 0 || n + 3 + size + 2 > sizeof(session->plaintext)) {
+            return -1;
+        }
+        put_u24(m + n, (uint32_t)size);
+        memcpy(m + n + 3, listener->certificate_chain + off, size);
+        put_u16(m + n + 3 + size, 0);
+        n += 3 + size + 2;
+        off += size;
+    }
+
+    m[0] = TLS_HS_CERTIFICATE;
+    put_u24(m + 1, (uint32_t)(n - 4));
+    m[4] = 0; // certificate_request_context
+    put_u24(m + 5, (uint32_t)(n - 8));
+    return tls_send_handshake(session, n);
+}
+
+static int tls_send_certificate_verify(orion_tls_session_t *session)
+{
+    uint8_t content[64 + sizeof(tls_server_verify_context) + TLS_HASH_SIZE];
+    uint8_t *m = session->plaintext;
+
+    memset(content, 0x20, 64);
+    memcpy(content + 64, tls_server_verify_context, sizeof(tls_server_verify_context));
+    tls_transcript_hash(session, content + 64 + sizeof(tls_server_verify_context));
+
+    // The private key stays in the keyring
+    if (orion_keyring_sign(session->listener->key_handle, content, sizeof(content), m + 8) != 0) {
+        klog_error(KLOG_CAT_KERNEL, "TLS: keyring refused to sign CertificateVerify");
+        return -1;
+    }
+
+    m[0] = TLS_HS_CERTIFICATE_VERIFY;
+    put_u24(m + 1, 4 + ORION_KEYRING_SIGNATURE_SIZE);
+    put_u16(m + 4, TLS_SIGNATURE_ED25519);
+    put_u16(m + 6, ORION_KEYRING_SIGNATURE_SIZE);
+    return tls_send_handshake(session, 8 + ORION_KEYRING_SIGNATURE_SIZE);
+}
+
+static int tls_send_finished(orion_tls_session_t *session, const uint8_t base_key[TLS_HASH_SIZE])
+{
+    uint8_t finished_key[TLS_HASH_SIZE];
+    uint8_t transcript[TLS_HASH_SIZE];
+    orion_hmac_sha256_ctx_t hmac;
+    uint8_t *m = session->plaintext;
+
+    tls_expand_label(base_key, "finished", NULL, 0, finished_key, sizeof(finished_key));
+    tls_transcript_hash(session, transcript);
+    orion_hmac_sha256_init(&hmac, finished_key, sizeof(finished_key));
+    orion_hmac_sha256_update(&hmac, transcript, sizeof(transcript));
+    orion_hmac_sha256_final(&hmac, m + 4);
+    orion_crypto_wipe(finished_key, sizeof(finished_key));
+
+    m[0] = TLS_HS_FINISHED;
+    put_u24(m + 1, TLS_HASH_SIZE);
+    return tls_send_handshake(session, 4 + TLS_HASH_SIZE);
+}
+
+// Check a Finished message of the peer against the transcript before it
+static int tls_check_finished(const orion_tls_session_t *session, const uint8_t base_key[TLS_HASH_SIZE],
+                              const uint8_t *msg, size_t len)
+{
+    uint8_t finished_key[TLS_HASH_SIZE];
+    uint8_t transcript[TLS_HASH_SIZE];
+    uint8_t expected[TLS_HASH_SIZE];
+    orion_hmac_sha256_ctx_t hmac;
+
+    if (len != 4 + TLS_HASH_SIZE) {
+        return ORION_TLS_ALERT_DECODE_ERROR;
+    }
+
+    tls_expand_label(base_key, "finished", NULL, 0, finished_key, sizeof(finished_key));
+    tls_transcript_hash(session, transcript);
+    orion_hmac_sha256_init(&hmac, finished_key, sizeof(finished_key));
+    orion_hmac_sha256_update(&hmac, transcript, sizeof(transcript));
+    orion_hmac_sha256_final(&hmac, expected);
+    orion_crypto_wipe(finished_key, sizeof(finished_key));
+
+    return orion_crypto_compare(expected, msg + 4, TLS_HASH_SIZE) == 0 ? 0 : ORION_TLS_ALERT_DECRYPT_ERROR;
+}
+
+static void tls_handle_client_hello(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    tls_reader_t r, vector, extensions;
+    const uint8_t *session_id;
+    const uint8_t *client_share = NULL;
+    size_t session_id_len;
+    bool tls13 = false, cipher = false, ed25519 = false, null_compression = false;
+
+    reader_init(&r, msg + 4, len - 4);
+    reader_uint(&r, 2); // legacy_version
+    reader_bytes(&r, 32);
+    session_id_len = reader_uint(&r, 1);
+    session_id = reader_bytes(&r, session_id_len);
+    if (session_id_len > 32) {
+        tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+        return;
+    }
+
+    reader_vector(&r, 2, &vector);
+    while (!vector.error && vector.off < vector.len) {
+        cipher |= reader_uint(&vector, 2) == TLS_CIPHER_CHACHA20_POLY1305_SHA256;
+    }
+    reader_vector(&r, 1, &vector);
+    while (!vector.error && vector.off < vector.len) {
+        null_compression |= reader_uint(&vector, 1) == 0;
+    }
+    if (r.error || r.off == r.len) {
+        // No extensions at all: a client without TLS 1.3
+        tls_fail(session, r.error ? ORION_TLS_ALERT_DECODE_ERROR : ORION_TLS_ALERT_PROTOCOL_VERSION);
+        return;
+    }
+
+    reader_vector(&r, 2, &extensions);
+    while (!extensions.error && extensions.off < extensions.len) {
+        uint32_t type = reader_uint(&extensions, 2);
+        tls_reader_t ext, list;
+        reader_vector(&extensions, 2, &ext);
+        reader_init(&list, NULL, 0);
+
+        switch (type) {
+        case TLS_EXT_SUPPORTED_VERSIONS:
+            reader_vector(&ext, 1, &list);
+            while (!list.error && list.off < list.len) {
+                tls13 |= reader_uint(&list, 2) == TLS_VERSION_13;
+            }
+            break;
+        case TLS_EXT_SIGNATURE_ALGORITHMS:
+            reader_vector(&ext, 2, &list);
+            while (!list.error && list.off < list.len) {
+                ed25519 |= reader_uint(&list, 2) == TLS_SIGNATURE_ED25519;
+            }
+            break;
+        case TLS_EXT_KEY_SHARE:
+            reader_vector(&ext, 2, &list);
+            while (!list.error && list.off < list.len) {
+                uint32_t group = reader_uint(&list, 2);
+                tls_reader_t key;
+                reader_vector(&list, 2, &key);
+                if (group == TLS_GROUP_X25519 && key.len == ORION_X25519_KEY_SIZE && !key.error) {
+                    client_share = key.data;
+                }
+            }
+            break;
+        default:
+            break;
+        }
+        if (ext.error || list.error) {
+            extensions.error = true;
+        }
+    }
+
+    if (extensions.error || r.off != r.len) {
+        tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+        return;
+    }
+    if (!tls13) {
+        tls_fail(session, ORION_TLS_ALERT_PROTOCOL_VERSION);
+        return;
+    }
+    if (!null_compression) {
+        tls_fail(session, ORION_TLS_ALERT_ILLEGAL_PARAMETER);
+        return;
+    }
+    if (!cipher || !ed25519 || !client_share) {
+        // HelloRetryRequest is not implemented: the client must offer X25519 upfront
+        tls_fail(session, ORION_TLS_ALERT_HANDSHAKE_FAILURE);
+        return;
+    }
+
+    /* ========================================================================
+     * Key exchange and handshake secrets
+     * ======================================================================== */
+
+    uint8_t private_key[ORION_X25519_KEY_SIZE];
+    uint8_t public_key[ORION_X25519_KEY_SIZE];
+    uint8_t shared[ORION_X25519_KEY_SIZE];
+    uint8_t zeros[TLS_HASH_SIZE] = {0};
+    uint8_t secret[TLS_HASH_SIZE];
+    uint8_t derived[TLS_HASH_SIZE];
+    uint8_t handshake_secret[TLS_HASH_SIZE];
+    uint8_t server_secret[TLS_HASH_SIZE];
+    uint8_t transcript[TLS_HASH_SIZE];
+    int failed;
+
+    tls_random(private_key, sizeof(private_key));
+    orion_x25519(public_key, private_key, NULL);
+    failed = orion_x25519(shared, private_key, client_share);
+    orion_crypto_wipe(private_key, sizeof(private_key));
+    if (failed) {
+        tls_fail(session, ORION_TLS_ALERT_ILLEGAL_PARAMETER);
+        return;
+    }
+
+    orion_sha256_update(&session->transcript, msg, len);
+    if (tls_send_server_hello(session, session_id, session_id_len, public_key) != 0) {
+        tls_fail(session, ORION_TLS_ALERT_INTERNAL_ERROR);
+        return;
+    }
+    if (session_id_len > 0) {
+        // Middlebox compatibility mode (RFC 8446 appendix D.4)
+        uint8_t change_cipher_spec = 1;
+        tls_write_record(session, TLS_CONTENT_CHANGE_CIPHER_SPEC, &change_cipher_spec, 1);
+    }
+
+    orion_hkdf_extract(zeros, sizeof(zeros), zeros, sizeof(zeros), secret);
+    tls_derive_empty(secret, derived);
+    orion_hkdf_extract(derived, sizeof(derived), shared, sizeof(shared), handshake_secret);
+    orion_crypto_wipe(shared, sizeof(shared));
+
+    tls_transcript_hash(session, transcript);
+    tls_expand_label(handshake_secret, "c hs traffic", transcript, sizeof(transcript),
+                     session->client_handshake_secret, TLS_HASH_SIZE);
+    tls_expand_label(handshake_secret, "s hs traffic", transcript, sizeof(transcript),
+                     server_secret, sizeof(server_secret));
+    tls_set_traffic(&session->write, server_secret);
+    tls_set_traffic(&session->read, session->client_handshake_secret);
+
+    /* ========================================================================
+     * Server flight
+     * ======================================================================== */
+
+    uint8_t *m = session->plaintext;
+    m[0] = TLS_HS_ENCRYPTED_EXTENSIONS;
+    put_u24(m + 1, 2);
+    put_u16(m + 4, 0);
+    failed = tls_send_handshake(session, 6) ||
+             tls_send_certificate(session) ||
+             tls_send_certificate_verify(session) ||
+             tls_send_finished(session, server_secret);
+
+    if (!failed) {
+        // Application secrets cover the transcript up to the server Finished
+        tls_derive_empty(handshake_secret, derived);
+        orion_hkdf_extract(derived, sizeof(derived), zeros, sizeof(zeros), secret);
+        tls_transcript_hash(session, transcript);
+        tls_expand_label(secret, "c ap traffic", transcript, sizeof(transcript),
+                         session->client_application_secret, TLS_HASH_SIZE);
+        tls_expand_label(secret, "s ap traffic", transcript, sizeof(transcript),
+                         server_secret, sizeof(server_secret));
+        tls_expand_label(secret, "exp master", transcript, sizeof(transcript),
+                         session->exporter_secret, TLS_HASH_SIZE);
+        tls_set_traffic(&session->write, server_secret);
+        session->state = ORION_TLS_STATE_WAIT_FINISHED;
+    }
+
+    orion_crypto_wipe(secret, sizeof(secret));
+    orion_crypto_wipe(handshake_secret, sizeof(handshake_secret));
+    orion_crypto_wipe(server_secret, sizeof(server_secret));
+    if (failed) {
+        tls_fail(session, ORION_TLS_ALERT_INTERNAL_ERROR);
+    }
+}
+
+static void tls_handle_finished(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    int alert = tls_check_finished(session, session->client_handshake_secret, msg, len);
+    if (alert != 0) {
+        tls_fail(session, alert);
+        return;
+    }
+
+    tls_set_traffic(&session->read, session->client_application_secret);
+    orion_crypto_wipe(session->client_handshake_secret, TLS_HASH_SIZE);
+    orion_crypto_wipe(session->client_application_secret, TLS_HASH_SIZE);
+    session->state = ORION_TLS_STATE_CONNECTED;
+    session->listener->handshakes++;
+}
+
+static void tls_handle_key_update(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    if (len != 5 || msg[4] > 1) {
+        tls_fail(session, len != 5 ? ORION_TLS_ALERT_DECODE_ERROR : ORION_TLS_ALERT_ILLEGAL_PARAMETER);
+        return;
+    }
+
+    tls_update_traffic(&session->read);
+    if (msg[4] == 1) {
+        // update_requested: answer under the old keys, then switch
+        uint8_t reply[5] = {TLS_HS_KEY_UPDATE, 0, 0, 1, 0};
+        if (tls_write_record(session, TLS_CONTENT_HANDSHAKE, reply, sizeof(reply)) != 0) {
+            tls_fail(session, ORION_TLS_ALERT_INTERNAL_ERROR);
+            return;
+        }
+        tls_update_traffic(&session->write);
+    }
+}
+
+/* ============================================================================
+ * Client Handshake
+ * ============================================================================ */
+
+static int tls_send_client_hello(orion_tls_session_t *session, const uint8_t public_key[ORION_X25519_KEY_SIZE])
+{
+    uint8_t *m = session->plaintext;
+    uint8_t address[4];
+    size_t name_len = strlen(session->server_name);
+    size_t n = 4;
+    size_t extensions;
+
+    put_u16(m + n, TLS_VERSION_12);
+    n += 2;
+    tls_random(m + n, 32);
+    n += 32;
+    // Middlebox compatibility mode (RFC 8446 appendix D.4)
+    m[n++] = sizeof(session->session_id);
+    memcpy(m + n, session->session_id, sizeof(session->session_id));
+    n += sizeof(session->session_id);
+    put_u16(m + n, 2);
+    put_u16(m + n + 2, TLS_CIPHER_CHACHA20_POLY1305_SHA256);
+    n += 4;
+    m[n++] = 1;
+    m[n++] = 0;
+
+    extensions = n;
+    n += 2;
+    if (!tls_parse_ipv4(session->server_name, address)) {
+        // server_name carries host names only
+        put_u16(m + n, TLS_EXT_SERVER_NAME);
+        put_u16(m + n + 2, (uint32_t)(name_len + 5));
+        put_u16(m + n + 4, (uint32_t)(name_len + 3));
+        m[n + 6] = 0; // host_name
+        put_u16(m + n + 7, (uint32_t)name_len);
+        memcpy(m + n + 9, session->server_name, name_len);
+        n += 9 + name_len;
+    }
+    put_u16(m + n, TLS_EXT_SUPPORTED_GROUPS);
+    put_u16(m + n + 2, 4);
+    put_u16(m + n + 4, 2);
+    put_u16(m + n + 6, TLS_GROUP_X25519);
+    n += 8;
+    put_u16(m + n, TLS_EXT_SIGNATURE_ALGORITHMS);
+    put_u16(m + n + 2, 4);
+    put_u16(m + n + 4, 2);
+    put_u16(m + n + 6, TLS_SIGNATURE_ED25519);
+    n += 8;
+    put_u16(m + n, TLS_EXT_SUPPORTED_VERSIONS);
+    put_u16(m + n + 2, 3);
+    m[n + 4] = 2;
+    put_u16(m + n + 5, TLS_VERSION_13);
+    n += 7;
+    put_u16(m + n, TLS_EXT_KEY_SHARE);
+    put_u16(m + n + 2, 38);
+    put_u16(m + n + 4, 36);
+    put_u16(m + n + 6, TLS_GROUP_X25519);
+    put_u16(m + n + 8, ORION_X25519_KEY_SIZE);
+    memcpy(m + n + 10, public_key, ORION_X25519_KEY_SIZE);
+    n += 42;
+    put_u16(m + extensions, (uint32_t)(n - extensions - 2));
+
+    m[0] = TLS_HS_CLIENT_HELLO;
+    put_u24(m + 1, (uint32_t)(n - 4));
+    return tls_send_handshake(session, n);
+}
+
+static void tls_handle_server_hello(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    tls_reader_t r, session_id, extensions;
+    const uint8_t *random;
+    const uint8_t *server_share = NULL;
+    uint32_t cipher, compression;
+    bool tls13 = false;
+
+    reader_init(&r, msg + 4, len - 4);
+    reader_uint(&r, 2); // legacy_version
+    random = reader_bytes(&r, 32);
+    reader_vector(&r, 1, &session_id);
+    cipher = reader_uint(&r, 2);
+    compression = reader_uint(&r, 1);
+    reader_vector(&r, 2, &extensions);
+    while (!extensions.error && extensions.off < extensions.len) {
+        uint32_t type = reader_uint(&extensions, 2);
+        tls_reader_t ext, key;
+        reader_vector(&extensions, 2, &ext);
+        reader_init(&key, NULL, 0);
+
+        switch (type) {
+        case TLS_EXT_SUPPORTED_VERSIONS:
+            tls13 = reader_uint(&ext, 2) == TLS_VERSION_13;
+            break;
+        case TLS_EXT_KEY_SHARE:
+            if (reader_uint(&ext, 2) == TLS_GROUP_X25519) {
+                reader_vector(&ext, 2, &key);
+                if (key.len == ORION_X25519_KEY_SIZE && !key.error) {
+                    server_share = key.data;
+                }
+            }
+            break;
+        default:
+            break;
+        }
+        if (ext.error || key.error) {
+            extensions.error = true;
+        }
+    }
+
+    if (r.error || extensions.error || r.off != r.len) {
+        tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+        return;
+    }
+    if (memcmp(random, tls_hello_retry_random, sizeof(tls_hello_retry_random)) == 0) {
+        // Only X25519 is offered; asking for another group means no overlap
+        tls_fail(session, ORION_TLS_ALERT_HANDSHAKE_FAILURE);
+        return;
+    }
+    if (!tls13) {
+        tls_fail(session, ORION_TLS_ALERT_PROTOCOL_VERSION);
+        return;
+    }
+    if (cipher != TLS_CIPHER_CHACHA20_POLY1305_SHA256 || compression != 0 || !server_share ||
+        session_id.len != sizeof(session->session_id) ||
+        memcmp(session_id.data, session->session_id, sizeof(session->session_id)) != 0) {
+        tls_fail(session, ORION_TLS_ALERT_ILLEGAL_PARAMETER);
+        return;
+    }
+
+    uint8_t shared[ORION_X25519_KEY_SIZE];
+    uint8_t zeros[TLS_HASH_SIZE] = {0};
+    uint8_t secret[TLS_HASH_SIZE];
+    uint8_t derived[TLS_HASH_SIZE];
+    uint8_t handshake_secret[TLS_HASH_SIZE];
+    uint8_t transcript[TLS_HASH_SIZE];
+    int failed;
+
+    failed = orion_x25519(shared, session->private_key, server_share);
+    orion_crypto_wipe(session->private_key, sizeof(session->private_key));
+    if (failed) {
+        tls_fail(session, ORION_TLS_ALERT_ILLEGAL_PARAMETER);
+        return;
+    }
+
+    orion_sha256_update(&session->transcript, msg, len);
+    orion_hkdf_extract(zeros, sizeof(zeros), zeros, sizeof(zeros), secret);
+    tls_derive_empty(secret, derived);
+    orion_hkdf_extract(derived, sizeof(derived), shared, sizeof(shared), handshake_secret);
+    orion_crypto_wipe(shared, sizeof(shared));
+
+    tls_transcript_hash(session, transcript);
+    tls_expand_label(handshake_secret, "c hs traffic", transcript, sizeof(transcript),
+                     session->client_handshake_secret, TLS_HASH_SIZE);
+    tls_expand_label(handshake_secret, "s hs traffic", transcript, sizeof(transcript),
+                     session->server_handshake_secret, TLS_HASH_SIZE);
+    tls_set_traffic(&session->read, session->server_handshake_secret);
+
+    // Application secrets need the transcript up to the server Finished
+    tls_derive_empty(handshake_secret, derived);
+    orion_hkdf_extract(derived, sizeof(derived), zeros, sizeof(zeros), session->master_secret);
+    orion_crypto_wipe(secret, sizeof(secret));
+    orion_crypto_wipe(handshake_secret, sizeof(handshake_secret));
+    session->state = ORION_TLS_STATE_WAIT_ENCRYPTED_EXTENSIONS;
+}
+
+static void tls_handle_certificate(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    tls_certificate_t chain[TLS_MAX_CHAIN];
+    tls_reader_t r, context, list;
+    size_t count = 0;
+
+    reader_init(&r, msg + 4, len - 4);
+    reader_vector(&r, 1, &context);
+    reader_vector(&r, 3, &list);
+    while (!list.error && list.off < list.len) {
+        tls_reader_t entry, extensions;
+        reader_vector(&list, 3, &entry);
+        reader_vector(&list, 2, &extensions);
+        if (list.error) {
+            break;
+        }
+        if (count == TLS_MAX_CHAIN || tls_parse_certificate(entry.data, entry.len, &chain[count]) != 0) {
+            tls_fail(session, ORION_TLS_ALERT_BAD_CERTIFICATE);
+            return;
+        }
+        count++;
+    }
+
+    if (r.error || list.error || r.off != r.len || context.len != 0 || count == 0) {
+        tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+        return;
+    }
+
+    int alert = tls_verify_chain(session, chain, count);
+    if (alert != 0) {
+        klog_error(KLOG_CAT_KERNEL, "TLS: certificate of %s rejected (alert %d)", session->server_name, alert);
+        tls_fail(session, (uint8_t)alert);
+        return;
+    }
+
+    memcpy(session->server_key, chain[0].public_key, sizeof(session->server_key));
+    orion_sha256_update(&session->transcript, msg, len);
+    session->state = ORION_TLS_STATE_WAIT_CERTIFICATE_VERIFY;
+}
+
+static void tls_handle_certificate_verify(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    uint8_t content[64 + sizeof(tls_server_verify_context) + TLS_HASH_SIZE];
+    tls_reader_t r, signature;
+    uint32_t scheme;
+
+    reader_init(&r, msg + 4, len - 4);
+    scheme = reader_uint(&r, 2);
+    reader_vector(&r, 2, &signature);
+    if (r.error || r.off != r.len) {
+        tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+        return;
+    }
+    if (scheme != TLS_SIGNATURE_ED25519 || signature.len != ORION_KEYRING_SIGNATURE_SIZE) {
+        tls_fail(session, ORION_TLS_ALERT_ILLEGAL_PARAMETER);
+        return;
+    }
+
+    memset(content, 0x20, 64);
+    memcpy(content + 64, tls_server_verify_context, sizeof(tls_server_verify_context));
+    tls_transcript_hash(session, content + 64 + sizeof(tls_server_verify_context));
+    if (orion_keyring_verify(session->server_key, content, sizeof(content), signature.data) != 0) {
+        tls_fail(session, ORION_TLS_ALERT_DECRYPT_ERROR);
+        return;
+    }
+
+    orion_sha256_update(&session->transcript, msg, len);
+    session->state = ORION_TLS_STATE_WAIT_SERVER_FINISHED;
+}
+
+static void tls_handle_server_finished(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    uint8_t server_secret[TLS_HASH_SIZE];
+    uint8_t transcript[TLS_HASH_SIZE];
+    uint8_t change_cipher_spec = 1;
+    int failed;
+
+    int alert = tls_check_finished(session, session->server_handshake_secret, msg, len);
+    if (alert != 0) {
+        tls_fail(session, alert);
+        return;
+    }
+    orion_sha256_update(&session->transcript, msg, len);
+
+    tls_transcript_hash(session, transcript);
+    tls_expand_label(session->master_secret, "c ap traffic", transcript, sizeof(transcript),
+                     session->client_application_secret, TLS_HASH_SIZE);
+    tls_expand_label(session->master_secret, "s ap traffic", transcript, sizeof(transcript),
+                     server_secret, sizeof(server_secret));
+    tls_expand_label(session->master_secret, "exp master", transcript, sizeof(transcript),
+                     session->exporter_secret, TLS_HASH_SIZE);
+
+    // Compatibility change_cipher_spec, then Finished under the handshake keys
+    failed = tls_write_record(session, TLS_CONTENT_CHANGE_CIPHER_SPEC, &change_cipher_spec, 1);
+    tls_set_traffic(&session->write, session->client_handshake_secret);
+    failed = failed || tls_send_finished(session, session->client_handshake_secret);
+
+    tls_set_traffic(&session->write, session->client_application_secret);
+    tls_set_traffic(&session->read, server_secret);
+    orion_crypto_wipe(session->master_secret, TLS_HASH_SIZE);
+    orion_crypto_wipe(server_secret, sizeof(server_secret));
+    orion_crypto_wipe(session->client_handshake_secret, TLS_HASH_SIZE);
+    orion_crypto_wipe(session->server_handshake_secret, TLS_HASH_SIZE);
+    orion_crypto_wipe(session->client_application_secret, TLS_HASH_SIZE);
+    if (failed) {
+        tls_fail(session, ORION_TLS_ALERT_INTERNAL_ERROR);
+        return;
+    }
+    session->state = ORION_TLS_STATE_CONNECTED;
+}
+
+static void tls_handle_handshake_message(orion_tls_session_t *session, const uint8_t *msg, size_t len)
+{
+    switch (session->state) {
+    case ORION_TLS_STATE_WAIT_CLIENT_HELLO:
+        if (msg[0] != TLS_HS_CLIENT_HELLO) {
+            break;
+        }
+        tls_handle_client_hello(session, msg, len);
+        return;
+    case ORION_TLS_STATE_WAIT_FINISHED:
+        if (msg[0] != TLS_HS_FINISHED) {
+            break;
+        }
+        tls_handle_finished(session, msg, len);
+        return;
+    case ORION_TLS_STATE_CONNECTED:
+        if (msg[0] == TLS_HS_NEW_SESSION_TICKET && !session->listener) {
+            return; // No resumption
+        }
+        if (msg[0] != TLS_HS_KEY_UPDATE) {
+            break;
+        }
+        tls_handle_key_update(session, msg, len);
+        return;
+    case ORION_TLS_STATE_WAIT_SERVER_HELLO:
+        if (msg[0] != TLS_HS_SERVER_HELLO) {
+            break;
+        }
+        tls_handle_server_hello(session, msg, len);
+        return;
+    case ORION_TLS_STATE_WAIT_ENCRYPTED_EXTENSIONS:
+        if (msg[0] != TLS_HS_ENCRYPTED_EXTENSIONS) {
+            break;
+        }
+        // Nothing the client offered changes its behavior
+        orion_sha256_update(&session->transcript, msg, len);
+        session->state = ORION_TLS_STATE_WAIT_CERTIFICATE;
+        return;
+    case ORION_TLS_STATE_WAIT_CERTIFICATE:
+        if (msg[0] != TLS_HS_CERTIFICATE) {
+            break;
+        }
+        tls_handle_certificate(session, msg, len);
+        return;
+    case ORION_TLS_STATE_WAIT_CERTIFICATE_VERIFY:
+        if (msg[0] != TLS_HS_CERTIFICATE_VERIFY) {
+            break;
+        }
+        tls_handle_certificate_verify(session, msg, len);
+        return;
+    case ORION_TLS_STATE_WAIT_SERVER_FINISHED:
+        if (msg[0] != TLS_HS_FINISHED) {
+            break;
+        }
+        tls_handle_server_finished(session, msg, len);
+        return;
+    default:
+        return;
+    }
+    tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+}
+
+// States left once the peer switched to new keys for what follows
+static bool tls_read_keys_change(orion_tls_state_t state)
+{
+    return state == ORION_TLS_STATE_WAIT_CLIENT_HELLO || state == ORION_TLS_STATE_WAIT_FINISHED ||
+           state == ORION_TLS_STATE_WAIT_SERVER_HELLO || state == ORION_TLS_STATE_WAIT_SERVER_FINISHED;
+}
+
+static void tls_handle_handshake_data(orion_tls_session_t *session, const uint8_t *data, size_t len)
+{
+    if (len > sizeof(session->handshake) - session->handshake_len) {
+        tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+        return;
+    }
+    memcpy(session->handshake + session->handshake_len, data, len);
+    session->handshake_len += len;
+
+    while (session->handshake_len >= 4) {
+        size_t msg_len = ((size_t)session->handshake[1] << 16) |
+                         ((size_t)session->handshake[2] << 8) | session->handshake[3];
+        if (msg_len > TLS_MAX_HANDSHAKE) {
+            tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+            return;
+        }
+        if (session->handshake_len < 4 + msg_len) {
+            return;
+        }
+
+        bool rekeys = tls_read_keys_change(session->state);
+        orion_tls_state_t before = session->state;
+        tls_handle_handshake_message(session, session->handshake, 4 + msg_len);
+        if (session->state == ORION_TLS_STATE_FAILED) {
+            return;
+        }
+
+        session->handshake_len -= 4 + msg_len;
+        memmove(session->handshake, session->handshake + 4 + msg_len, session->handshake_len);
+
+        // Messages preceding a key change must end on a record boundary
+        if (rekeys && session->state != before && session->handshake_len != 0) {
+            tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+            return;
+        }
+    }
+}
+
+static void tls_handle_alert(orion_tls_session_t *session, const uint8_t *data, size_t len)
+{
+    if (len != 2) {
+        tls_fail(session, ORION_TLS_ALERT_DECODE_ERROR);
+        return;
+    }
+    if (data[1] == ORION_TLS_ALERT_CLOSE_NOTIFY) {
+        session->state = ORION_TLS_STATE_CLOSED;
+        return;
+    }
+
+    klog_debug(KLOG_CAT_KERNEL, "TLS alert from peer: %u", data[1]);
+    if (session->listener && session->state != ORION_TLS_STATE_CONNECTED) {
+        session->listener->handshake_failures++;
+    }
+    session->state = ORION_TLS_STATE_FAILED;
+}
+
+static void tls_process_record(orion_tls_session_t *session)
+{
+    uint8_t type = session->record[0];
+    uint8_t *body = session->record + TLS_RECORD_HEADER;
+    size_t len = session->record_len - TLS_RECORD_HEADER;
+
+    if (session->record[1] != 0x03) {
+        tls_fail(session, ORION_TLS_ALERT_PROTOCOL_VERSION);
+        return;
+    }
+
+    // Compatibility change_cipher_spec records are dropped during the handshake
+    if (type == TLS_CONTENT_CHANGE_CIPHER_SPEC) {
+        if (session->state == ORION_TLS_STATE_CONNECTED || len != 1 || body[0] != 1) {
+            tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+        }
+        return;
+    }
+
+    if (!session->read.active) {
+        if (len > ORION_TLS_MAX_PLAINTEXT) {
+            tls_fail(session, ORION_TLS_ALERT_RECORD_OVERFLOW);
+        } else if (type == TLS_CONTENT_HANDSHAKE) {
+            tls_handle_handshake_data(session, body, len);
+        } else if (type == TLS_CONTENT_ALERT) {
+            tls_handle_alert(session, body, len);
+        } else {
+            tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+        }
+        return;
+    }
+
+    if (type != TLS_CONTENT_APPLICATION_DATA) {
+        tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+        return;
+    }
+
+    uint8_t nonce[ORION_CHACHA20_NONCE_SIZE];
+    tls_nonce(&session->read, nonce);
+    if (orion_chacha20_poly1305_open(session->read.key, nonce, session->record, TLS_RECORD_HEADER,
+                                     body, len, session->plaintext) != 0) {
+        tls_fail(session, ORION_TLS_ALERT_BAD_RECORD_MAC);
+        return;
+    }
+    session->read.sequence++;
+
+    // Strip the zero padding to find the real content type
+    size_t inner_len = len - ORION_POLY1305_TAG_SIZE;
+    while (inner_len > 0 && session->plaintext[inner_len - 1] == 0) {
+        inner_len--;
+    }
+    if (inner_len == 0) {
+        tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+        return;
+    }
+    uint8_t inner_type = session->plaintext[--inner_len];
+    if (inner_len > ORION_TLS_MAX_PLAINTEXT) {
+        tls_fail(session, ORION_TLS_ALERT_RECORD_OVERFLOW);
+        return;
+    }
+
+    switch (inner_type) {
+    case TLS_CONTENT_HANDSHAKE:
+        tls_handle_handshake_data(session, session->plaintext, inner_len);
+        break;
+    case TLS_CONTENT_ALERT:
+        tls_handle_alert(session, session->plaintext, inner_len);
+        break;
+    case TLS_CONTENT_APPLICATION_DATA:
+        if (session->state != ORION_TLS_STATE_CONNECTED) {
+            tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+            return;
+        }
+        session->plaintext_off = 0;
+        session->plaintext_len = inner_len;
+        return;
+    default:
+        tls_fail(session, ORION_TLS_ALERT_UNEXPECTED_MESSAGE);
+        break;
+    }
+
+    if (session->state != ORION_TLS_STATE_FAILED) {
+        orion_crypto_wipe(session->plaintext, inner_len);
+    }
+}
+
+/* ============================================================================
+ * Listener Configuration
+ * ============================================================================ */
+
+orion_tls_listener_t *orion_tls_listener_create(uint64_t certificate_handle, uint64_t key_handle)
+{
+    orion_tls_listener_t *listener = kmalloc(sizeof(orion_tls_listener_t));
+    if (!listener) {
+        klog_error(KLOG_CAT_KERNEL, "Failed to allocate memory for TLS listener");
+        return NULL;
+    }
+    memset(listener, 0, sizeof(orion_tls_listener_t));
+
+    listener->certificate_chain = kmalloc(ORION_TLS_MAX_CERTIFICATE_CHAIN);
+    if (!listener->certificate_chain) {
+        kfree(listener);
+        return NULL;
+    }
+
+    int result = orion_keyring_read(certificate_handle, listener->certificate_chain,
+                                    ORION_TLS_MAX_CERTIFICATE_CHAIN, &listener->certificate_chain_len);
+    if (result != 0 || der_sequence_size(listener->certificate_chain, listener->certificate_chain_len) == 0) {
+        klog_error(KLOG_CAT_KERNEL, "TLS: certificate chain unavailable from keyring (%d)", result);
+        kfree(listener->certificate_chain);
+        kfree(listener);
+        return NULL;
+    }
+
+    listener->certificate_handle = certificate_handle;
+    listener->key_handle = key_handle;
+    listener->refcount = 1;
+
+    klog_info(KLOG_CAT_KERNEL, "TLS listener configured (%zu byte certificate chain)",
+              listener->certificate_chain_len);
+    return listener;
+}
+
+void orion_tls_listener_put(orion_tls_listener_t *listener)
+{
+    if (!listener) {
+        return;
+    }
+
+    spinlock_acquire(&tls_listener_lock);
+    bool last = --listener->refcount == 0;
+    spinlock_release(&tls_listener_lock);
+
+    if (last) {
+        kfree(listener->certificate_chain);
+        kfree(listener);
+    }
+}
+
+/* ============================================================================
+ * Session API
+ * ============================================================================ */
+
+orion_tls_session_t *orion_tls_session_create(orion_tls_listener_t *listener)
+{
+    if (!listener) {
+        return NULL;
+    }
+
+    orion_tls_session_t *session = kmalloc(sizeof(orion_tls_session_t));
+    if (!session) {
+        klog_error(KLOG_CAT_KERNEL, "Failed to allocate memory for TLS session");
+        return NULL;
+    }
+    memset(session, 0, sizeof(orion_tls_session_t));
+
+    spinlock_acquire(&tls_listener_lock);
+    listener->refcount++;
+    spinlock_release(&tls_listener_lock);
+
+    session->listener = listener;
+    session->state = ORION_TLS_STATE_WAIT_CLIENT_HELLO;
+    orion_sha256_init(&session->transcript);
+    return session;
+}
+
+orion_tls_session_t *orion_tls_client_create(uint64_t anchor_handle, const char *server_name)
+{
+    size_t name_len = server_name ? strlen(server_name) : 0;
+    uint8_t public_key[ORION_X25519_KEY_SIZE];
+    tls_certificate_t anchor;
+    size_t anchor_len = 0;
+
+    if (name_len == 0 || name_len > ORION_TLS_MAX_SERVER_NAME) {
+        return NULL;
+    }
+
+    uint8_t *certificate = kmalloc(ORION_TLS_MAX_CERTIFICATE_CHAIN);
+    if (!certificate) {
+        return NULL;
+    }
+    int result = orion_keyring_read(anchor_handle, certificate, ORION_TLS_MAX_CERTIFICATE_CHAIN, &anchor_len);
+    if (result != 0 || tls_parse_certificate(certificate, anchor_len, &anchor) != 0) {
+        klog_error(KLOG_CAT_KERNEL, "TLS: trust anchor unavailable from keyring (%d)", result);
+        kfree(certificate);
+        return NULL;
+    }
+
+    orion_tls_session_t *session = kmalloc(sizeof(orion_tls_session_t));
+    if (!session) {
+        klog_error(KLOG_CAT_KERNEL, "Failed to allocate memory for TLS session");
+        kfree(certificate);
+        return NULL;
+    }
+    memset(session, 0, sizeof(orion_tls_session_t));
+    memcpy(session->anchor_key, anchor.public_key, sizeof(session->anchor_key));
+    memcpy(session->server_name, server_name, name_len);
+    kfree(certificate);
+
+    session->state = ORION_TLS_STATE_WAIT_SERVER_HELLO;
+    orion_sha256_init(&session->transcript);
+    tls_random(session->session_id, sizeof(session->session_id));
+    tls_random(session->private_key, sizeof(session->private_key));
+    orion_x25519(public_key, session->private_key, NULL);
+    if (tls_send_client_hello(session, public_key) != 0) {
+        orion_tls_session_destroy(session);
+        return NULL;
+    }
+    return session;
+}
+
+void orion_tls_session_destroy(orion_tls_session_t *session)
+{
+    if (!session) {
+        return;
+    }
+
+    orion_tls_listener_put(session->listener);
+    orion_crypto_wipe(session, sizeof(orion_tls_session_t));
+    kfree(session);
+}
+
+ssize_t orion_tls_session_input(orion_tls_session_t *session, const void *data, size_t len)
+{
+    const uint8_t *bytes = data;
+    size_t consumed = 0;
+
+    if (!session || !data) {
+        return -1;
+    }
+    if (session->state == ORION_TLS_STATE_FAILED) {
+        return -1;
+    }
+
+    // Hold back input while decrypted data waits for the application
+    while (session->plaintext_off == session->plaintext_len) {
+        if (session->state == ORION_TLS_STATE_CLOSED) {
+            // Nothing after close_notify is meaningful
+            return len;
+        }
+
+        size_t need = TLS_RECORD_HEADER;
+        if (session->record_len >= TLS_RECORD_HEADER) {
+            size_t body = ((size_t)session->record[3] << 8) | session->record[4];
+            if (body > ORION_TLS_MAX_RECORD - TLS_RECORD_HEADER) {
+                tls_fail(session, ORION_TLS_ALERT_RECORD_OVERFLOW);
+                return -1;
+            }
+            need += body;
+        }
+
+        if (session->record_len < need) {
+            if (consumed == len) {
+                break;
+            }
+            size_t chunk = need - session->record_len;
+            if (chunk > len - consumed) {
+                chunk = len - consumed;
+            }
+            memcpy(session->record + session->record_len, bytes + consumed, chunk);
+            session->record_len += chunk;
+            consumed += chunk;
+            continue;
+        }
+
+        tls_process_record(session);
+        session->record_len = 0;
+        if (session->state == ORION_TLS_STATE_FAILED) {
+            return -1;
+        }
+    }
+
+    return consumed;
+}
+
+size_t orion_tls_session_output(orion_tls_session_t *session, void *data, size_t len)
+{
+    if (!session || !data) {
+        return 0;
+    }
+
+    size_t copy_len = len < session->output_len ? len : session->output_len;
+    memcpy(data, session->output, copy_len);
+    session->output_len -= copy_len;
+    memmove(session->output, session->output + copy_len, session->output_len);
+    return copy_len;
+}
+
+ssize_t orion_tls_session_read(orion_tls_session_t *session, void *data, size_t len)
+{
+    if (!session || !data) {
+        return -1;
+    }
+
+    size_t pending = session->plaintext_len - session->plaintext_off;
+    if (pending == 0) {
+        bool finished = session->state == ORION_TLS_STATE_CLOSED || session->state == ORION_TLS_STATE_FAILED;
+        return finished ? -1 : 0;
+    }
+
+    size_t copy_len = len < pending ? len : pending;
+    memcpy(data, session->plaintext + session->plaintext_off, copy_len);
+    session->plaintext_off += copy_len;
+    if (session->plaintext_off == session->plaintext_len) {
+        orion_crypto_wipe(session->plaintext, session->plaintext_len);
+        session->plaintext_off = 0;
+        session->plaintext_len = 0;
+    }
+    return copy_len;
+}
+
+ssize_t orion_tls_session_write(orion_tls_session_t *session, const void *data, size_t len)
+{
+    const uint8_t *bytes = data;
+    size_t accepted = 0;
+
+    if (!session || !data || session->state != ORION_TLS_STATE_CONNECTED) {
+        return -1;
+    }
+
+    while (accepted < len) {
+        size_t chunk = len - accepted;
+        if (chunk > ORION_TLS_MAX_PLAINTEXT) {
+            chunk = ORION_TLS_MAX_PLAINTEXT;
+        }
+        if (tls_write_record(session, TLS_CONTENT_APPLICATION_DATA, bytes + accepted, chunk) != 0) {
+            break;
+        }
+        accepted += chunk;
+    }
+    return accepted;
+}
+
+void orion_tls_session_close(orion_tls_session_t *session)
+{
+    if (!session || session->state == ORION_TLS_STATE_FAILED) {
+        return;
+    }
+
+    tls_send_alert(session, TLS_ALERT_LEVEL_WARNING, ORION_TLS_ALERT_CLOSE_NOTIFY);
+    session->state = ORION_TLS_STATE_CLOSED;
+}
+
+orion_tls_state_t orion_tls_session_get_state(const orion_tls_session_t *session)
+{
+    return session ? session->state : ORION_TLS_STATE_FAILED;
+}
+
+bool orion_tls_session_handshaking(const orion_tls_session_t *session)
+{
+    return session && session->state != ORION_TLS_STATE_CONNECTED && session->state != ORION_TLS_STATE_CLOSED &&
+           session->state != ORION_TLS_STATE_FAILED;
+}
+
+int orion_tls_session_export(const orion_tls_session_t *session, const char *label, uint8_t *out,
+                             size_t out_len)
+{
+    uint8_t empty_hash[TLS_HASH_SIZE];
+    uint8_t secret[TLS_HASH_SIZE];
+    orion_sha256_ctx_t ctx;
+
+    if (!session || !label || !out || session->state != ORION_TLS_STATE_CONNECTED ||
+        strlen(label) > ORION_TLS_MAX_EXPORTER_LABEL || out_len == 0 || out_len > ORION_TLS_MAX_EXPORT) {
+        return -1;
+    }
+
+    // HKDF-Expand-Label(Derive-Secret(exporter_master_secret, label, ""),
+    //                   "exporter", Hash(""), out_len)
+    orion_sha256_init(&ctx);
+    orion_sha256_final(&ctx, empty_hash);
+    tls_expand_label(session->exporter_secret, label, empty_hash, sizeof(empty_hash), secret, sizeof(secret));
+    tls_expand_label(secret, "exporter", empty_hash, sizeof(empty_hash), out, out_len);
+    orion_crypto_wipe(secret, sizeof(secret));
+    return 0;
+}
@@ -0,0 +1,218 @@
+/*
+ * Orion Operating System - TLS Termination Offload
+ *
+ * The network server can terminate TLS on behalf of a listening socket so
+ * that applications (the Prometheus exporter, management APIs, small
+ * services) only ever see a plaintext stream. The certificate chain and
+ * the private key of a listener live in the keyring: the chain is read
+ * once when the listener is configured, the key is only used through the
+ * keyring SIGN operation.
+ *
+ * Supported profile: TLS 1.3 only, TLS_CHACHA20_POLY1305_SHA256, X25519
+ * key exchange and Ed25519 certificates, no client authentication, no
+ * session resumption.
+ *
+ * The same engine runs the client side of connections opened by local
+ * processes (package and image downloads). The server's chain must lead
+ * to a CA certificate held in the keyring, the trust anchor: every
+ * certificate has to be valid at the current wall clock time and signed by
+ * the next one, intermediates must be CAs, and the leaf has to name the
+ * host in its subjectAltName.
+ *
+ * Either side can export keying material bound to the session once the
+ * handshake completed (RFC 8446 section 7.5), so an application protocol
+ * running inside the stream can tie its own signatures to this connection.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_TLS_OFFLOAD_H
+#define ORION_TLS_OFFLOAD_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+/* ============================================================================
+ * TLS Constants
+ * ============================================================================ */
+
+#define ORION_TLS_MAX_PLAINTEXT 16384                            // Largest record payload
+#define ORION_TLS_MAX_RECORD (5 + ORION_TLS_MAX_PLAINTEXT + 256) // Largest protected record
+#define ORION_TLS_MAX_CERTIFICATE_CHAIN 8192                     // DER chain, leaf first
+#define ORION_TLS_MAX_SERVER_NAME 255                            // Host name a client connects to
+#define ORION_TLS_MAX_EXPORTER_LABEL 32                          // Longest exporter label
+#define ORION_TLS_MAX_EXPORT 64                                  // Most keying material per export
+
+// Alert descriptions (RFC 8446 section 6)
+#define ORION_TLS_ALERT_CLOSE_NOTIFY 0
+#define ORION_TLS_ALERT_UNEXPECTED_MESSAGE 10
+#define ORION_TLS_ALERT_BAD_RECORD_MAC 20
+#define ORION_TLS_ALERT_RECORD_OVERFLOW 22
+#define ORION_TLS_ALERT_HANDSHAKE_FAILURE 40
+#define ORION_TLS_ALERT_BAD_CERTIFICATE 42
+#define ORION_TLS_ALERT_CERTIFICATE_EXPIRED 45
+#define ORION_TLS_ALERT_ILLEGAL_PARAMETER 47
+#define ORION_TLS_ALERT_UNKNOWN_CA 48
+#define ORION_TLS_ALERT_DECODE_ERROR 50
+#define ORION_TLS_ALERT_DECRYPT_ERROR 51
+#define ORION_TLS_ALERT_PROTOCOL_VERSION 70
+#define ORION_TLS_ALERT_INTERNAL_ERROR 80
+#define ORION_TLS_ALERT_MISSING_EXTENSION 109
+
+    typedef enum
+    {
+        ORION_TLS_STATE_WAIT_CLIENT_HELLO = 0, // Nothing received yet
+        ORION_TLS_STATE_WAIT_FINISHED,         // Server flight sent, waiting for client Finished
+        ORION_TLS_STATE_CONNECTED,             // Application data flows
+        ORION_TLS_STATE_CLOSED,                // close_notify received or sent
+        ORION_TLS_STATE_FAILED,                // Fatal alert sent or received
+
+        // Client side
+        ORION_TLS_STATE_WAIT_SERVER_HELLO,         // ClientHello sent
+        ORION_TLS_STATE_WAIT_ENCRYPTED_EXTENSIONS, // Handshake keys installed
+        ORION_TLS_STATE_WAIT_CERTIFICATE,
+        ORION_TLS_STATE_WAIT_CERTIFICATE_VERIFY, // Chain accepted
+        ORION_TLS_STATE_WAIT_SERVER_FINISHED     // Server key proven
+    } orion_tls_state_t;
+
+    /* ============================================================================
+     * TLS Listener Configuration
+     * ============================================================================ */
+
+    typedef struct orion_tls_listener
+    {
+        uint64_t certificate_handle; // Keyring handle of the DER certificate chain
+        uint64_t key_handle;         // Keyring handle of the Ed25519 private key
+        uint8_t *certificate_chain;  // Chain read from the keyring
+        size_t certificate_chain_len;
+        uint32_t refcount; // Listener socket plus live sessions
+
+        // Statistics
+        uint64_t handshakes;         // Completed handshakes
+        uint64_t handshake_failures; // Handshakes ended by an alert
+    } orion_tls_listener_t;
+
+    typedef struct orion_tls_session orion_tls_session_t;
+
+    /**
+     * @brief Configure TLS termination for a listener
+     * @param certificate_handle Keyring handle of the certificate chain
+     * @param key_handle Keyring handle of the private key (USE grant)
+     * @return Listener configuration or NULL on error
+     */
+    orion_tls_listener_t *orion_tls_listener_create(uint64_t certificate_handle, uint64_t key_handle);
+
+    /**
+     * @brief Drop a reference to a listener configuration
+     * @param listener Listener configuration
+     */
+    void orion_tls_listener_put(orion_tls_listener_t *listener);
+
+    /* ============================================================================
+     * TLS Sessions
+     * ============================================================================ */
+
+    /**
+     * @brief Create the server side of a TLS session
+     * @param listener Listener the connection was accepted on
+     * @return Session or NULL on error
+     */
+    orion_tls_session_t *orion_tls_session_create(orion_tls_listener_t *listener);
+
+    /**
+     * @brief Create the client side of a TLS session
+     * @param anchor_handle Keyring handle of the trusted CA certificate
+     * @param server_name Host name or dotted IPv4 address of the server
+     * @return Session with its ClientHello queued for output, or NULL on error
+     */
+    orion_tls_session_t *orion_tls_client_create(uint64_t anchor_handle, const char *server_name);
+
+    /**
+     * @brief Destroy a session and wipe its keys
+     * @param session TLS session
+     */
+    void orion_tls_session_destroy(orion_tls_session_t *session);
+
+    /**
+     * @brief Feed bytes received from the peer
+     * @param session TLS session
+     * @param data Received bytes
+     * @param len Number of bytes
+     * @return Number of bytes consumed (the rest must be fed again later),
+     *         or negative value once the session failed
+     */
+    ssize_t orion_tls_session_input(orion_tls_session_t *session, const void *data, size_t len);
+
+    /**
+     * @brief Take protected bytes to transmit to the peer
+     * @param session TLS session
+     * @param data Output buffer
+     * @param len Output capacity
+     * @return Number of bytes copied
+     */
+    size_t orion_tls_session_output(orion_tls_session_t *session, void *data, size_t len);
+
+    /**
+     * @brief Read decrypted application data
+     * @param session TLS session
+     * @param data Data buffer
+     * @param len Buffer length
+     * @return Number of bytes read, 0 if none is pending, negative value
+     *         once the session is closed or failed and fully drained
+     */
+    ssize_t orion_tls_session_read(orion_tls_session_t *session, void *data, size_t len);
+
+    /**
+     * @brief Protect application data for transmission
+     * @param session TLS session
+     * @param data Plaintext
+     * @param len Plaintext length
+     * @return Number of bytes accepted (limited by output space), negative
+     *         value if the session cannot carry application data
+     */
+    ssize_t orion_tls_session_write(orion_tls_session_t *session, const void *data, size_t len);
+
+    /**
+     * @brief Queue a close_notify alert
+     * @param session TLS session
+     */
+    void orion_tls_session_close(orion_tls_session_t *session);
+
+    /**
+     * @brief Get the session state
+     * @param session TLS session
+     * @return Session state
+     */
+    orion_tls_state_t orion_tls_session_get_state(const orion_tls_session_t *session);
+
+    /**
+     * @brief Tell whether the handshake is still running
+     * @param session TLS session
+     * @return true until application data can flow or the session ended
+     */
+    bool orion_tls_session_handshaking(const orion_tls_session_t *session);
+
+    /**
+     * @brief Export keying material (RFC 8446 section 7.5, empty context)
+     * @param session TLS session
+     * @param label Exporter label, at most ORION_TLS_MAX_EXPORTER_LABEL bytes
+     * @param out Keying material
+     * @param out_len Bytes wanted, at most ORION_TLS_MAX_EXPORT
+     * @return 0 on success, negative value unless the session is connected
+     */
+    int orion_tls_session_export(const orion_tls_session_t *session, const char *label, uint8_t *out,
+                                 size_t out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_TLS_OFFLOAD_H
@@ -0,0 +1,149 @@
+/*
+ * Orion Operating System - Clock Discipline
+ *
+ * Turns the samples of a poll round into a wall clock correction. The
+ * sample with the smallest round-trip delay is kept, as its offset has
+ * the smallest error bound. Offsets up to STEP_THRESHOLD_NS are slewed so
+ * that the clock never jumps or runs backwards under TLS sessions and
+ * audit records; larger ones are stepped. Once synchronized, an offset
+ * beyond PANIC_THRESHOLD_NS is taken for a bad source and ignored.
+ *
+ * The poll interval doubles while the clock stays within STABLE_OFFSET_NS
+ * and after rounds nobody answered, and halves when corrections grow.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use crate::sntp::{Sample, NS_PER_SEC};
+
+/// Largest offset corrected by slewing
+pub const STEP_THRESHOLD_NS: i64 = 128_000_000;
+
+/// Largest offset accepted once synchronized
+pub const PANIC_THRESHOLD_NS: i64 = 1000 * NS_PER_SEC as i64;
+
+/// Offset under which the clock counts as stable
+pub const STABLE_OFFSET_NS: i64 = 16_000_000;
+
+pub const MIN_POLL_S: u32 = 64;
+pub const MAX_POLL_S: u32 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Correction {
+    Slew(i64),
+    Step(i64),
+    /// Offset beyond the panic threshold, clock left alone
+    Rejected(i64),
+}
+
+pub struct Discipline {
+    synced: bool,
+    poll_s: u32,
+    pub slews: u32,
+    pub steps: u32,
+    pub rejected: u32,
+}
+
+impl Discipline {
+    pub fn new() -> Self {
+        Self { synced: false, poll_s: MIN_POLL_S, slews: 0, steps: 0, rejected: 0 }
+    }
+
+    /// Sample of the round with the smallest delay, the lower stratum on ties
+    pub fn select(samples: &[Sample]) -> Option<Sample> {
+        samples.iter().copied().min_by_key(|sample| (sample.delay_ns, sample.stratum))
+    }
+
+    /// Correction for the selected sample of a round
+    pub fn apply(&mut self, sample: &Sample) -> Correction {
+        let magnitude = sample.offset_ns.saturating_abs();
+        if self.synced && magnitude > PANIC_THRESHOLD_NS {
+            self.rejected += 1;
+            return Correction::Rejected(sample.offset_ns);
+        }
+        self.synced = true;
+
+        if magnitude > STEP_THRESHOLD_NS {
+            self.steps += 1;
+            self.poll_s = MIN_POLL_S;
+            return Correction::Step(sample.offset_ns);
+        }
+
+        self.slews += 1;
+        self.poll_s = if magnitude < STABLE_OFFSET_NS {
+            (self.poll_s * 2).min(MAX_POLL_S)
+        } else {
+            (self.poll_s / 2).max(MIN_POLL_S)
+        };
+        Correction::Slew(sample.offset_ns)
+    }
+
+    /// Back off after a round without any usable reply or a rate kiss
+    pub fn back_off(&mut self) {
+        self.poll_s = (self.poll_s * 2).min(MAX_POLL_S);
+    }
+
+    pub fn synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Seconds until the next round
+    pub fn poll_interval_s(&self) -> u32 {
+        self.poll_s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sntp::LEAP_NONE;
+
+    fn sample(server: u32, offset_ns: i64, delay_ns: i64, stratum: u8) -> Sample {
+        Sample { server, offset_ns, delay_ns, stratum, leap: LEAP_NONE }
+    }
+
+    #[test]
+    fn test_selects_lowest_delay() {
+        let samples = [
+            sample(1, 5_000_000, 40_000_000, 1),
+            sample(2, 2_000_000, 12_000_000, 3),
+            sample(3, 1_000_000, 12_000_000, 2),
+        ];
+        assert_eq!(Discipline::select(&samples).unwrap().server, 3);
+        assert_eq!(Discipline::select(&[]), None);
+    }
+
+    #[test]
+    fn test_slews_steps_and_rejects() {
+        let mut discipline = Discipline::new();
+
+        // The first sync may be arbitrarily far off, e.g. a wrong RTC
+        let far = 3 * 3600 * NS_PER_SEC as i64;
+        assert_eq!(discipline.apply(&sample(1, far, 1, 1)), Correction::Step(far));
+        assert!(discipline.synced());
+        assert_eq!(discipline.poll_interval_s(), MIN_POLL_S);
+
+        assert_eq!(discipline.apply(&sample(1, -5_000_000, 1, 1)), Correction::Slew(-5_000_000));
+        assert_eq!(discipline.poll_interval_s(), 2 * MIN_POLL_S);
+        assert_eq!(discipline.apply(&sample(1, 60_000_000, 1, 1)), Correction::Slew(60_000_000));
+        assert_eq!(discipline.poll_interval_s(), MIN_POLL_S);
+        assert_eq!(discipline.apply(&sample(1, -200_000_000, 1, 1)), Correction::Step(-200_000_000));
+
+        assert_eq!(discipline.apply(&sample(1, far, 1, 1)), Correction::Rejected(far));
+        assert_eq!((discipline.slews, discipline.steps, discipline.rejected), (2, 2, 1));
+    }
+
+    #[test]
+    fn test_backs_off() {
+        let mut discipline = Discipline::new();
+        for _ in 0..10 {
+            discipline.back_off();
+        }
+        assert_eq!(discipline.poll_interval_s(), MAX_POLL_S);
+        assert!(!discipline.synced());
+    }
+}
@@ -0,0 +1,366 @@
+/*
+ * Orion Operating System - Time Server
+ *
+ * SNTPv4 client keeping the kernel wall clock on UTC. Each round queries
+ * the configured servers one after the other over a UDP socket of the
+ * network server; the best sample is handed to the discipline (see
+ * discipline.rs), which slews the clock when it can and steps it when it
+ * must, through SYS_CLOCK_ADJUST. A leap second announced by the selected
+ * server is scheduled in the kernel for the end of the UTC day. Steps and
+ * leap seconds are recorded in the audit log, since they affect the
+ * timestamps of every later record and TLS certificate checks.
+ *
+ * Servers are set by administrators over IPC; status is available to
+ * readers (see protocol.rs).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+use orion_sys::{audit_emit, clock_adjust, clock_get};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod discipline;
+mod protocol;
+mod sntp;
+mod udp;
+
+use discipline::{Correction, Discipline};
+use protocol::*;
+use sntp::{Reply, Sample, SntpError, LEAP_DELETE, LEAP_INSERT, LEAP_NONE, NS_PER_SEC, NTP_PORT, PACKET_SIZE};
+use udp::UdpSocket;
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 10_000_000;
+
+/// Time allowed for a server to answer
+const QUERY_TIMEOUT_NS: u64 = 2 * NS_PER_SEC;
+
+// Clocks and SYS_CLOCK_ADJUST operations (mirror of wallclock.h)
+const CLOCK_ID_MONOTONIC: u32 = 0;
+const CLOCK_ID_REALTIME: u32 = 1;
+const CLOCK_ADJUST_SLEW: u32 = 1;
+const CLOCK_ADJUST_STEP: u32 = 2;
+const CLOCK_ADJUST_LEAP: u32 = 3;
+
+/// Audit event types emitted by the time server (user range, see capabilities.c)
+const AUDIT_CLOCK_STEP: u32 = 0x1101;
+const AUDIT_CLOCK_LEAP: u32 = 0x1102;
+
+struct Server {
+    ip: u32,
+    /// Shift register of the last eight rounds, bit 0 set when answered
+    reach: u8,
+    denied: bool,
+    last: Option<Sample>,
+}
+
+/// Request in flight
+struct Query {
+    server: usize,
+    transmit: u64,
+    sent_ns: u64,
+    deadline_ns: u64,
+}
+
+/// Poll round in progress
+struct Round {
+    next: usize,
+    query: Option<Query>,
+    samples: Vec<Sample>,
+    rate_limited: bool,
+}
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn realtime_ns() -> u64 {
+    clock_get(CLOCK_ID_REALTIME).unwrap_or(0)
+}
+
+fn format_ip(ip: u32) -> String {
+    format!("{}.{}.{}.{}", ip >> 24, (ip >> 16) & 0xFF, (ip >> 8) & 0xFF, ip & 0xFF)
+}
+
+struct TimeServer {
+    servers: Vec<Server>,
+    discipline: Discipline,
+    round: Option<Round>,
+    next_round_ns: u64,
+    socket: Option<UdpSocket>,
+    selected: Option<Sample>,
+    leap: u8,
+    leaps: u32,
+    last_sync_ns: u64,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl TimeServer {
+    fn new() -> Self {
+        Self {
+            servers: Vec::new(),
+            discipline: Discipline::new(),
+            round: None,
+            next_round_ns: 0,
+            socket: None,
+            selected: None,
+            leap: LEAP_NONE,
+            leaps: 0,
+            last_sync_ns: 0,
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+
+            let now = monotonic_ns();
+            if self.round.is_none() && !self.servers.is_empty() && now >= self.next_round_ns {
+                self.start_round();
+            }
+            if let Some(round) = self.round.take() {
+                self.advance_round(round, now);
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn start_round(&mut self) {
+        if self.socket.is_none() {
+            match UdpSocket::bind(IpcChannel::connect("net")) {
+                Ok(socket) => self.socket = Some(socket),
+                Err(_) => {
+                    // Network server not ready, try again next round
+                    self.discipline.back_off();
+                    self.schedule_next_round();
+                    return;
+                }
+            }
+        }
+        for server in self.servers.iter_mut() {
+            server.reach <<= 1;
+        }
+        self.round = Some(Round { next: 0, query: None, samples: Vec::new(), rate_limited: false });
+    }
+
+    fn advance_round(&mut self, mut round: Round, now: u64) {
+        if let Some(query) = round.query.take() {
+            match self.receive(&query) {
+                Some(Ok(sample)) => {
+                    let server = &mut self.servers[query.server];
+                    server.reach |= 1;
+                    server.last = Some(sample);
+                    round.samples.push(sample);
+                }
+                Some(Err(SntpError::KissOfDeath(code))) => match &code {
+                    b"RATE" => round.rate_limited = true,
+                    b"DENY" | b"RSTR" => self.servers[query.server].denied = true,
+                    _ => {}
+                },
+                // Unsynchronized or malformed: no sample from this server
+                Some(Err(_)) => {}
+                None if now < query.deadline_ns => {
+                    round.query = Some(query);
+                    self.round = Some(round);
+                    return;
+                }
+                None => {}
+            }
+        }
+
+        while round.next < self.servers.len() && self.servers[round.next].denied {
+            round.next += 1;
+        }
+        if round.next == self.servers.len() {
+            self.finish_round(round);
+            return;
+        }
+
+        let server = round.next;
+        round.next += 1;
+        round.query = self.send_query(server, now);
+        self.round = Some(round);
+    }
+
+    fn send_query(&mut self, server: usize, now: u64) -> Option<Query> {
+        let socket = self.socket.as_mut()?;
+        let sent_ns = realtime_ns();
+        let transmit = sntp::to_ntp(sent_ns);
+        socket.send_to(self.servers[server].ip, NTP_PORT, &sntp::request(transmit)).ok()?;
+        Some(Query { server, transmit, sent_ns, deadline_ns: now + QUERY_TIMEOUT_NS })
+    }
+
+    /// Reply to the query in flight, if one arrived
+    fn receive(&mut self, query: &Query) -> Option<Result<Sample, SntpError>> {
+        let ip = self.servers[query.server].ip;
+        let socket = self.socket.as_mut()?;
+        loop {
+            let (source, port, data) = socket.recv_from(PACKET_SIZE).ok()?;
+            let received_ns = realtime_ns();
+            if source != ip || port != NTP_PORT {
+                continue;
+            }
+            match Reply::parse(&data, query.transmit) {
+                Ok(reply) => return Some(Ok(Sample::new(ip, &reply, query.sent_ns, received_ns))),
+                // Late reply to an earlier query
+                Err(SntpError::Mismatch) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    fn finish_round(&mut self, round: Round) {
+        match Discipline::select(&round.samples) {
+            None => self.discipline.back_off(),
+            Some(best) => {
+                let applied = match self.discipline.apply(&best) {
+                    Correction::Slew(offset_ns) => clock_adjust(CLOCK_ADJUST_SLEW, offset_ns as u64).is_ok(),
+                    Correction::Step(offset_ns) => {
+                        let applied = clock_adjust(CLOCK_ADJUST_STEP, offset_ns as u64).is_ok();
+                        let record = format!(
+                            "clock-step offset_ns={} server={} stratum={} applied={}",
+                            offset_ns,
+                            format_ip(best.server),
+                            best.stratum,
+                            applied
+                        );
+                        let _ = audit_emit(AUDIT_CLOCK_STEP, record.as_bytes());
+                        applied
+                    }
+                    Correction::Rejected(_) => false,
+                };
+                if applied {
+                    self.selected = Some(best);
+                    self.last_sync_ns = realtime_ns();
+                    self.announce_leap(best.leap, best.server);
+                }
+            }
+        }
+        if round.rate_limited {
+            self.discipline.back_off();
+        }
+        self.schedule_next_round();
+    }
+
+    /// Schedule (or withdraw) the leap second announced by the time source
+    fn announce_leap(&mut self, leap: u8, server: u32) {
+        if leap == self.leap || clock_adjust(CLOCK_ADJUST_LEAP, leap as u64).is_err() {
+            return;
+        }
+        if leap != LEAP_NONE {
+            self.leaps += 1;
+        }
+        self.leap = leap;
+
+        let indicator = match leap {
+            LEAP_INSERT => "insert",
+            LEAP_DELETE => "delete",
+            _ => "none",
+        };
+        let record = format!("clock-leap indicator={} server={}", indicator, format_ip(server));
+        let _ = audit_emit(AUDIT_CLOCK_LEAP, record.as_bytes());
+    }
+
+    fn schedule_next_round(&mut self) {
+        self.next_round_ns = monotonic_ns() + self.discipline.poll_interval_s() as u64 * NS_PER_SEC;
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match NtpRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            NtpRequest::SetServers { .. } => CAP_ADMIN,
+            _ => CAP_READ,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        match request {
+            NtpRequest::Status => self.encode_status(&mut payload),
+            NtpRequest::SetServers { servers } => {
+                self.servers =
+                    servers.into_iter().map(|ip| Server { ip, reach: 0, denied: false, last: None }).collect();
+                // Query the new servers right away
+                self.round = None;
+                self.next_round_ns = 0;
+            }
+            NtpRequest::ListServers => {
+                for server in self.servers.iter() {
+                    let flags = if server.denied { SERVER_FLAG_DENIED } else { 0 };
+                    let (offset_ns, delay_ns) =
+                        server.last.map_or((0, 0), |sample| (sample.offset_ns, sample.delay_ns));
+                    encode_server(server.ip, server.reach, flags, offset_ns, delay_ns, &mut payload);
+                }
+            }
+        }
+
+        self.ipc_channel.send(message.sender, &reply(STATUS_OK, &payload));
+    }
+
+    fn encode_status(&self, out: &mut Vec<u8>) {
+        let selected =
+            self.selected.unwrap_or(Sample { server: 0, offset_ns: 0, delay_ns: 0, stratum: 0, leap: LEAP_NONE });
+        out.extend_from_slice(&(self.discipline.synced() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.leap as u32).to_le_bytes());
+        out.extend_from_slice(&(selected.stratum as u32).to_le_bytes());
+        out.extend_from_slice(&self.discipline.poll_interval_s().to_le_bytes());
+        out.extend_from_slice(&selected.offset_ns.to_le_bytes());
+        out.extend_from_slice(&selected.delay_ns.to_le_bytes());
+        out.extend_from_slice(&self.last_sync_ns.to_le_bytes());
+        out.extend_from_slice(&selected.server.to_le_bytes());
+        out.extend_from_slice(&self.discipline.slews.to_le_bytes());
+        out.extend_from_slice(&self.discipline.steps.to_le_bytes());
+        out.extend_from_slice(&self.discipline.rejected.to_le_bytes());
+        out.extend_from_slice(&self.leaps.to_le_bytes());
+    }
+}
+
+fn main() {
+    let mut server = TimeServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
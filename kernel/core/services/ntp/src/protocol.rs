@@ -0,0 +1,116 @@
+/*
+ * Orion Operating System - Time Server Protocol
+ *
+ * IPC requests of the NTP client. All fields are little-endian; every
+ * message starts with a 32-bit opcode and every reply starts with a 32-bit
+ * signed status (0 or a negative errno).
+ *
+ *   STATUS        (none)                -> synced:u32 leap:u32 stratum:u32
+ *                                          poll:u32 offset:i64 delay:i64
+ *                                          last_sync:u64 server:u32
+ *                                          slews:u32 steps:u32
+ *                                          rejected:u32 leaps:u32
+ *   SET_SERVERS   count:u32 ip:u32...   -> (empty)
+ *   LIST_SERVERS  (none)                -> records: ip:u32 reach:u32
+ *                                          flags:u32 offset:i64 delay:i64
+ *
+ * Offsets and delays are nanoseconds from the last selected sample;
+ * last_sync is the wall clock time (ns since the Unix epoch) of the last
+ * correction, 0 before the first. `leap` is the indicator announced by the
+ * time source and `leaps` the number of leap seconds scheduled in the
+ * kernel. `reach` is the usual 8-bit shift register of answered polls.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_STATUS: u32 = 1;
+pub const OP_SET_SERVERS: u32 = 2;
+pub const OP_LIST_SERVERS: u32 = 3;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_EINVAL: i32 = -22;
+
+/// Servers queried in each round
+pub const MAX_SERVERS: usize = 8;
+
+// LIST_SERVERS flags
+/// Server answered with a DENY or RSTR kiss and is no longer queried
+pub const SERVER_FLAG_DENIED: u32 = 1 << 0;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NtpRequest {
+    Status,
+    SetServers { servers: Vec<u32> },
+    ListServers,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl NtpRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_STATUS => Some(NtpRequest::Status),
+            OP_SET_SERVERS => {
+                let count = read_u32(data, 4)? as usize;
+                if count > MAX_SERVERS {
+                    return None;
+                }
+                let servers = (0..count).map(|index| read_u32(data, 8 + 4 * index)).collect::<Option<Vec<u32>>>()?;
+                if servers.contains(&0) {
+                    return None;
+                }
+                Some(NtpRequest::SetServers { servers })
+            }
+            OP_LIST_SERVERS => Some(NtpRequest::ListServers),
+            _ => None,
+        }
+    }
+}
+
+/// LIST_SERVERS record
+pub fn encode_server(ip: u32, reach: u8, flags: u32, offset_ns: i64, delay_ns: i64, out: &mut Vec<u8>) {
+    out.extend_from_slice(&ip.to_le_bytes());
+    out.extend_from_slice(&(reach as u32).to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&offset_ns.to_le_bytes());
+    out.extend_from_slice(&delay_ns.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        assert_eq!(NtpRequest::decode(&OP_STATUS.to_le_bytes()), Some(NtpRequest::Status));
+
+        let mut message = Vec::new();
+        message.extend_from_slice(&OP_SET_SERVERS.to_le_bytes());
+        message.extend_from_slice(&2u32.to_le_bytes());
+        message.extend_from_slice(&0x0A00_0001u32.to_le_bytes());
+        message.extend_from_slice(&0xC0A8_0101u32.to_le_bytes());
+        assert_eq!(
+            NtpRequest::decode(&message),
+            Some(NtpRequest::SetServers { servers: alloc::vec![0x0A00_0001, 0xC0A8_0101] })
+        );
+        assert_eq!(NtpRequest::decode(&message[..12]), None);
+
+        message[4..8].copy_from_slice(&(MAX_SERVERS as u32 + 1).to_le_bytes());
+        assert_eq!(NtpRequest::decode(&message), None);
+        assert_eq!(NtpRequest::decode(&9u32.to_le_bytes()), None);
+    }
+}
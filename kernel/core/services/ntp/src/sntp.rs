@@ -0,0 +1,260 @@
+/*
+ * Orion Operating System - SNTPv4 Packets
+ *
+ * Client side of the Simple Network Time Protocol (RFC 4330): building a
+ * client request, validating a server reply and turning the four
+ * timestamps of an exchange into a clock offset and round-trip delay.
+ * NTP timestamps are 32.32 fixed-point seconds since 1900; they are
+ * converted to nanoseconds since the Unix epoch, the unit of the kernel
+ * wall clock, with era 1 (from February 2036) handled by pivoting on the
+ * top bit of the seconds field.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+/// Size of an NTP packet without extension fields or MAC
+pub const PACKET_SIZE: usize = 48;
+
+/// UDP port of NTP servers
+pub const NTP_PORT: u16 = 123;
+
+/// Seconds from 1900-01-01 (NTP epoch) to 1970-01-01 (Unix epoch)
+pub const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+pub const NS_PER_SEC: u64 = 1_000_000_000;
+
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+// Leap indicator values
+pub const LEAP_NONE: u8 = 0;
+pub const LEAP_INSERT: u8 = 1;
+pub const LEAP_DELETE: u8 = 2;
+pub const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// Highest stratum of a synchronized server
+pub const MAX_STRATUM: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SntpError {
+    /// Shorter than an NTP header
+    Truncated,
+    /// Not a server reply of a supported version
+    BadMode,
+    /// Originate timestamp does not echo our request (stale or forged)
+    Mismatch,
+    /// Stratum 0 reply carrying a kiss code such as "RATE" or "DENY"
+    KissOfDeath([u8; 4]),
+    /// Server clock is not synchronized (alarm leap indicator or stratum 16)
+    Unsynchronized,
+}
+
+/// Convert nanoseconds since the Unix epoch to an NTP timestamp
+pub fn to_ntp(unix_ns: u64) -> u64 {
+    let seconds = unix_ns / NS_PER_SEC + NTP_UNIX_OFFSET;
+    let fraction = ((unix_ns % NS_PER_SEC) << 32) / NS_PER_SEC;
+    ((seconds & 0xFFFF_FFFF) << 32) | fraction
+}
+
+/// Convert an NTP timestamp to nanoseconds since the Unix epoch
+///
+/// Seconds with the top bit clear belong to era 1, which covers 1968 to
+/// 2104; instants before the Unix epoch yield None.
+pub fn from_ntp(timestamp: u64) -> Option<u64> {
+    let mut seconds = timestamp >> 32;
+    if seconds & 0x8000_0000 == 0 {
+        seconds += 1 << 32;
+    }
+    let unix_seconds = seconds.checked_sub(NTP_UNIX_OFFSET)?;
+    let fraction_ns = ((timestamp & 0xFFFF_FFFF) * NS_PER_SEC) >> 32;
+    Some(unix_seconds * NS_PER_SEC + fraction_ns)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    ((read_u32(data, offset) as u64) << 32) | read_u32(data, offset + 4) as u64
+}
+
+/// Client request; `transmit` must be remembered to match the reply
+pub fn request(transmit: u64) -> [u8; PACKET_SIZE] {
+    let mut packet = [0u8; PACKET_SIZE];
+    packet[0] = (LEAP_NONE << 6) | (VERSION << 3) | MODE_CLIENT;
+    packet[40..48].copy_from_slice(&transmit.to_be_bytes());
+    packet
+}
+
+/// Validated server reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reply {
+    pub leap: u8,
+    pub stratum: u8,
+    pub reference_id: u32,
+    /// Server time at which the request arrived (T2)
+    pub receive_ns: u64,
+    /// Server time at which the reply left (T3)
+    pub transmit_ns: u64,
+}
+
+impl Reply {
+    /// Parse and check a reply to the request sent with `transmit`
+    pub fn parse(data: &[u8], transmit: u64) -> Result<Self, SntpError> {
+        if data.len() < PACKET_SIZE {
+            return Err(SntpError::Truncated);
+        }
+        let leap = data[0] >> 6;
+        let version = (data[0] >> 3) & 0x7;
+        let mode = data[0] & 0x7;
+        if mode != MODE_SERVER || !(1..=VERSION).contains(&version) {
+            return Err(SntpError::BadMode);
+        }
+        if read_u64(data, 24) != transmit {
+            return Err(SntpError::Mismatch);
+        }
+
+        let stratum = data[1];
+        if stratum == 0 {
+            let mut code = [0u8; 4];
+            code.copy_from_slice(&data[12..16]);
+            return Err(SntpError::KissOfDeath(code));
+        }
+        if leap == LEAP_UNSYNCHRONIZED || stratum > MAX_STRATUM {
+            return Err(SntpError::Unsynchronized);
+        }
+
+        let receive = read_u64(data, 32);
+        let transmit_time = read_u64(data, 40);
+        if receive == 0 || transmit_time == 0 {
+            return Err(SntpError::Unsynchronized);
+        }
+        Ok(Reply {
+            leap,
+            stratum,
+            reference_id: read_u32(data, 12),
+            receive_ns: from_ntp(receive).ok_or(SntpError::Unsynchronized)?,
+            transmit_ns: from_ntp(transmit_time).ok_or(SntpError::Unsynchronized)?,
+        })
+    }
+}
+
+/// Outcome of one exchange with a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// IPv4 address of the server
+    pub server: u32,
+    /// Correction to apply to the local clock
+    pub offset_ns: i64,
+    /// Round-trip delay, excluding the server processing time
+    pub delay_ns: i64,
+    pub stratum: u8,
+    pub leap: u8,
+}
+
+impl Sample {
+    /// Combine a reply with the local send (T1) and receive (T4) times
+    pub fn new(server: u32, reply: &Reply, sent_ns: u64, received_ns: u64) -> Self {
+        let t1 = sent_ns as i64;
+        let t2 = reply.receive_ns as i64;
+        let t3 = reply.transmit_ns as i64;
+        let t4 = received_ns as i64;
+        Sample {
+            server,
+            offset_ns: ((t2 - t1) + (t3 - t4)) / 2,
+            // Negative when the server timestamps are inconsistent
+            delay_ns: ((t4 - t1) - (t3 - t2)).max(0),
+            stratum: reply.stratum,
+            leap: reply.leap,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply_packet(leap: u8, stratum: u8, originate: u64, receive: u64, transmit: u64) -> [u8; PACKET_SIZE] {
+        let mut packet = [0u8; PACKET_SIZE];
+        packet[0] = (leap << 6) | (VERSION << 3) | MODE_SERVER;
+        packet[1] = stratum;
+        packet[12..16].copy_from_slice(b"GPS\0");
+        packet[24..32].copy_from_slice(&originate.to_be_bytes());
+        packet[32..40].copy_from_slice(&receive.to_be_bytes());
+        packet[40..48].copy_from_slice(&transmit.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_converts_timestamps() {
+        // 2025-08-01T00:00:00.5Z
+        let unix_ns = 1_754_006_400 * NS_PER_SEC + 500_000_000;
+        let ntp = to_ntp(unix_ns);
+        assert_eq!(ntp >> 32, 1_754_006_400 + NTP_UNIX_OFFSET);
+        assert_eq!(ntp & 0xFFFF_FFFF, 0x8000_0000);
+        assert_eq!(from_ntp(ntp), Some(unix_ns));
+
+        // Era 1: 2040-01-01 wraps the 32-bit seconds field
+        let unix_ns = 2_208_988_800 * NS_PER_SEC;
+        assert_eq!(to_ntp(unix_ns) >> 32, (2 * NTP_UNIX_OFFSET) & 0xFFFF_FFFF);
+        assert_eq!(from_ntp(to_ntp(unix_ns)), Some(unix_ns));
+
+        // 1968 (top bit set, era 0) is before the Unix epoch
+        assert_eq!(from_ntp(0x8000_0000u64 << 32), None);
+    }
+
+    #[test]
+    fn test_parses_replies() {
+        let sent = to_ntp(1_754_006_400 * NS_PER_SEC);
+        let request = request(sent);
+        assert_eq!(request[0], 0x23);
+        assert_eq!(read_u64(&request, 40), sent);
+
+        let receive = to_ntp(1_754_006_400 * NS_PER_SEC + 60_000_000);
+        let transmit = to_ntp(1_754_006_400 * NS_PER_SEC + 61_000_000);
+        let reply = Reply::parse(&reply_packet(LEAP_INSERT, 2, sent, receive, transmit), sent).unwrap();
+        assert_eq!(reply.leap, LEAP_INSERT);
+        assert_eq!(reply.stratum, 2);
+        assert_eq!(reply.reference_id, u32::from_be_bytes(*b"GPS\0"));
+
+        assert_eq!(Reply::parse(&request, sent), Err(SntpError::BadMode));
+        assert_eq!(Reply::parse(&request[..47], sent), Err(SntpError::Truncated));
+        assert_eq!(
+            Reply::parse(&reply_packet(LEAP_NONE, 2, sent + 1, receive, transmit), sent),
+            Err(SntpError::Mismatch)
+        );
+        assert_eq!(
+            Reply::parse(&reply_packet(LEAP_UNSYNCHRONIZED, 2, sent, receive, transmit), sent),
+            Err(SntpError::Unsynchronized)
+        );
+        assert_eq!(
+            Reply::parse(&reply_packet(LEAP_NONE, 16, sent, receive, transmit), sent),
+            Err(SntpError::Unsynchronized)
+        );
+
+        let mut kiss = reply_packet(LEAP_UNSYNCHRONIZED, 0, sent, 0, 0);
+        kiss[12..16].copy_from_slice(b"RATE");
+        assert_eq!(Reply::parse(&kiss, sent), Err(SntpError::KissOfDeath(*b"RATE")));
+    }
+
+    #[test]
+    fn test_computes_offset_and_delay() {
+        // Local clock 250 ms behind, 20 ms each way, 1 ms in the server
+        let base = 1_754_006_400 * NS_PER_SEC;
+        let reply = Reply {
+            leap: LEAP_NONE,
+            stratum: 1,
+            reference_id: 0,
+            receive_ns: base + 20_000_000 + 250_000_000,
+            transmit_ns: base + 21_000_000 + 250_000_000,
+        };
+        let sample = Sample::new(0x0A00_0001, &reply, base, base + 41_000_000);
+        assert_eq!(sample.offset_ns, 250_000_000);
+        assert_eq!(sample.delay_ns, 40_000_000);
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * Orion Operating System - Time Server UDP Socket
+ *
+ * Datagram socket of the network server (UDP_BIND, SENDTO and RECVFROM in
+ * services/net/socket_ipc.h). RECVFROM answers -EAGAIN instead of
+ * blocking, so replies are polled from the run loop.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_ipc::IpcChannel;
+
+// Socket opcodes of the network server
+const OP_CLOSE: u32 = 6;
+const OP_UDP_BIND: u32 = 7;
+const OP_SENDTO: u32 = 8;
+const OP_RECVFROM: u32 = 9;
+
+const STATUS_OK: i32 = 0;
+const STATUS_EIO: i32 = -5;
+
+pub struct UdpSocket {
+    channel: IpcChannel,
+    socket: u32,
+}
+
+fn call(channel: &mut IpcChannel, message: &[u8]) -> Result<Vec<u8>, i32> {
+    let reply = channel.call(message).map_err(|_| STATUS_EIO)?;
+    if reply.len() < 4 {
+        return Err(STATUS_EIO);
+    }
+    match i32::from_le_bytes([reply[0], reply[1], reply[2], reply[3]]) {
+        STATUS_OK => Ok(reply[4..].to_vec()),
+        status => Err(status),
+    }
+}
+
+impl UdpSocket {
+    /// Bind an ephemeral port on every interface
+    pub fn bind(mut channel: IpcChannel) -> Result<Self, i32> {
+        let mut message = Vec::with_capacity(10);
+        message.extend_from_slice(&OP_UDP_BIND.to_le_bytes());
+        message.extend_from_slice(&0u32.to_le_bytes());
+        message.extend_from_slice(&0u16.to_le_bytes());
+
+        let payload = call(&mut channel, &message)?;
+        let socket = payload.get(..4).ok_or(STATUS_EIO)?;
+        Ok(Self { socket: u32::from_le_bytes([socket[0], socket[1], socket[2], socket[3]]), channel })
+    }
+
+    pub fn send_to(&mut self, ip: u32, port: u16, data: &[u8]) -> Result<(), i32> {
+        let mut message = Vec::with_capacity(14 + data.len());
+        message.extend_from_slice(&OP_SENDTO.to_le_bytes());
+        message.extend_from_slice(&self.socket.to_le_bytes());
+        message.extend_from_slice(&ip.to_le_bytes());
+        message.extend_from_slice(&port.to_le_bytes());
+        message.extend_from_slice(data);
+        call(&mut self.channel, &message).map(|_| ())
+    }
+
+    /// Next queued datagram as (ip, port, data); Err(-EAGAIN) when none
+    pub fn recv_from(&mut self, max: usize) -> Result<(u32, u16, Vec<u8>), i32> {
+        let mut message = Vec::with_capacity(12);
+        message.extend_from_slice(&OP_RECVFROM.to_le_bytes());
+        message.extend_from_slice(&self.socket.to_le_bytes());
+        message.extend_from_slice(&(max as u32).to_le_bytes());
+
+        let payload = call(&mut self.channel, &message)?;
+        if payload.len() < 6 {
+            return Err(STATUS_EIO);
+        }
+        let ip = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        let port = u16::from_le_bytes([payload[4], payload[5]]);
+        Ok((ip, port, payload[6..].to_vec()))
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        let mut message = Vec::with_capacity(8);
+        message.extend_from_slice(&OP_CLOSE.to_le_bytes());
+        message.extend_from_slice(&self.socket.to_le_bytes());
+        let _ = call(&mut self.channel, &message);
+    }
+}
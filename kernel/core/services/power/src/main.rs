@@ -0,0 +1,231 @@
+/*
+ * Orion Operating System - Power Policy Server
+ *
+ * Watches the battery and AC adapter through the ACPI battery driver and
+ * applies the low-power policy (see policy.rs): subscribers hear about
+ * level changes and the adapter being plugged in or out, the display is
+ * dimmed through the GPU power modes while the charge is low, and the
+ * suspend orchestrator is asked to suspend the machine when it becomes
+ * critical.
+ *
+ * Reading the status or subscribing needs the read right on the power
+ * capability, changing the thresholds the write right.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_READ, CAP_WRITE};
+use orion_sys::clock_get;
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod policy;
+mod protocol;
+
+use policy::{Action, Policy, Reading, Thresholds};
+use protocol::*;
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 100_000_000;
+/// Pause between two battery readings
+const BATTERY_POLL_NS: u64 = 5_000_000_000;
+
+const MAX_SUBSCRIBERS: usize = 64;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+// Battery driver request (see drivers/char/src/acpi_battery.rs)
+const BATTERY_OP_READ: u32 = 1;
+
+// GPU power mode ioctl (see drivers/gpu/src/virtio_gpu.rs)
+const GPU_IOCTL_POWER_MODE: u32 = 0x04;
+const GPU_POWER_MODE_BALANCED: u8 = 1;
+const GPU_POWER_MODE_POWER_SAVING: u8 = 2;
+
+// Suspend orchestrator request: reason:u32
+const SUSPEND_OP_ENTER: u32 = 1;
+const SUSPEND_REASON_BATTERY_CRITICAL: u32 = 1;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+struct PowerServer {
+    policy: Policy,
+    reading: Option<Reading>,
+    next_poll_ns: u64,
+    subscribers: BTreeSet<u64>,
+    battery: IpcChannel,
+    gpu: IpcChannel,
+    suspend: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl PowerServer {
+    fn new() -> Self {
+        Self {
+            policy: Policy::new(Thresholds::DEFAULT),
+            reading: None,
+            next_poll_ns: 0,
+            subscribers: BTreeSet::new(),
+            battery: IpcChannel::connect("acpi-battery"),
+            gpu: IpcChannel::connect("virtio-gpu"),
+            suspend: IpcChannel::connect("suspend"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            let now = monotonic_ns();
+            if now >= self.next_poll_ns {
+                self.next_poll_ns = now + BATTERY_POLL_NS;
+                self.poll_battery();
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn read_battery(&mut self) -> Option<Reading> {
+        let response = self.battery.call(&BATTERY_OP_READ.to_le_bytes()).ok()?;
+        if response.len() < 4 + Reading::SIZE || response[..4] != STATUS_OK.to_le_bytes() {
+            return None;
+        }
+        Reading::decode(&response[4..])
+    }
+
+    fn poll_battery(&mut self) {
+        // A failed reading keeps the last one rather than guessing
+        let reading = match self.read_battery() {
+            Some(reading) => reading,
+            None => return,
+        };
+        self.reading = Some(reading);
+        for action in self.policy.update(&reading) {
+            self.apply(action, &reading);
+        }
+    }
+
+    fn apply(&mut self, action: Action, reading: &Reading) {
+        match action {
+            Action::Level(level) => {
+                self.notify(&event(EVENT_LEVEL, &[level.as_u32(), reading.percentage, reading.time_to_empty]))
+            }
+            Action::Ac(online) => self.notify(&event(EVENT_AC, &[online as u32, reading.percentage])),
+            Action::Dim(dim) => {
+                let mode = if dim { GPU_POWER_MODE_POWER_SAVING } else { GPU_POWER_MODE_BALANCED };
+                let mut request = GPU_IOCTL_POWER_MODE.to_le_bytes().to_vec();
+                request.push(mode);
+                let _ = self.gpu.post(&request);
+            }
+            Action::Suspend => {
+                let _ = self.suspend.post(&event(SUSPEND_OP_ENTER, &[SUSPEND_REASON_BATTERY_CRITICAL]));
+            }
+        }
+    }
+
+    fn notify(&mut self, message: &[u8]) {
+        for &subscriber in &self.subscribers {
+            self.ipc_channel.send(subscriber, message);
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match PowerRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            PowerRequest::SetPolicy { .. } => CAP_WRITE,
+            _ => CAP_READ,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let sender = message.sender;
+        let mut payload = Vec::new();
+        let status = match request {
+            PowerRequest::Status => match self.reading {
+                Some(reading) => {
+                    payload.extend_from_slice(&self.policy.level().as_u32().to_le_bytes());
+                    reading.encode(&mut payload);
+                    STATUS_OK
+                }
+                None => STATUS_EIO,
+            },
+            PowerRequest::GetPolicy => {
+                let thresholds = self.policy.thresholds();
+                for value in [thresholds.low, thresholds.critical, thresholds.hysteresis, thresholds.flags] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                STATUS_OK
+            }
+            PowerRequest::SetPolicy { thresholds } => {
+                if self.policy.set_thresholds(thresholds) {
+                    // Apply the new thresholds without waiting for the next poll
+                    self.next_poll_ns = 0;
+                    STATUS_OK
+                } else {
+                    STATUS_EINVAL
+                }
+            }
+            PowerRequest::Subscribe => {
+                if self.subscribers.len() >= MAX_SUBSCRIBERS && !self.subscribers.contains(&sender) {
+                    STATUS_ENOSPC
+                } else {
+                    self.subscribers.insert(sender);
+                    STATUS_OK
+                }
+            }
+            PowerRequest::Unsubscribe => {
+                self.subscribers.remove(&sender);
+                STATUS_OK
+            }
+        };
+        self.ipc_channel.send(sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    let mut server = PowerServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
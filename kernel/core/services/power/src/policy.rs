@@ -0,0 +1,276 @@
+/*
+ * Orion Operating System - Low-Power Policy
+ *
+ * Turns battery readings into a charge level and the actions taken when it
+ * changes. On battery power the level drops to Low and Critical as the
+ * charge falls under the configured thresholds and only climbs back once
+ * the charge is `hysteresis` points above them, so a reading wobbling
+ * around a threshold does not flap. Plugging in the AC adapter returns to
+ * Normal at once. Entering Low dims the display and entering Critical
+ * suspends the machine, once per discharge; the battery's own alarm counts
+ * as Critical whatever the percentage.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+// Policy flags
+pub const POLICY_DIM_ON_LOW: u32 = 1 << 0;
+pub const POLICY_SUSPEND_ON_CRITICAL: u32 = 1 << 1;
+
+// Reading state flags (mirror of drivers/char/src/acpi_battery.rs)
+pub const STATE_DISCHARGING: u32 = 1 << 0;
+pub const STATE_CHARGING: u32 = 1 << 1;
+pub const STATE_CRITICAL: u32 = 1 << 2;
+pub const STATE_FULL: u32 = 1 << 3;
+
+/// Charge level of the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Normal,
+    Low,
+    Critical,
+}
+
+impl Level {
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Level::Normal => 0,
+            Level::Low => 1,
+            Level::Critical => 2,
+        }
+    }
+}
+
+/// One reading of the battery driver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reading {
+    pub battery_present: bool,
+    pub ac_online: bool,
+    pub state: u32,
+    pub percentage: u32,
+    pub remaining_mwh: u32,
+    pub full_charge_mwh: u32,
+    pub design_mwh: u32,
+    pub rate_mw: i32,
+    pub voltage_mv: u32,
+    pub time_to_empty: u32,
+    pub time_to_full: u32,
+}
+
+impl Reading {
+    /// Fixed-size record of the battery driver's READ reply
+    pub const SIZE: usize = 44;
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let field = |index: usize| -> Option<u32> {
+            let bytes = data.get(index * 4..index * 4 + 4)?;
+            Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        Some(Self {
+            battery_present: field(0)? != 0,
+            ac_online: field(1)? != 0,
+            state: field(2)?,
+            percentage: field(3)?,
+            remaining_mwh: field(4)?,
+            full_charge_mwh: field(5)?,
+            design_mwh: field(6)?,
+            rate_mw: field(7)? as i32,
+            voltage_mv: field(8)?,
+            time_to_empty: field(9)?,
+            time_to_full: field(10)?,
+        })
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        for value in [
+            self.battery_present as u32,
+            self.ac_online as u32,
+            self.state,
+            self.percentage,
+            self.remaining_mwh,
+            self.full_charge_mwh,
+            self.design_mwh,
+            self.rate_mw as u32,
+            self.voltage_mv,
+            self.time_to_empty,
+            self.time_to_full,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    pub low: u32,
+    pub critical: u32,
+    pub hysteresis: u32,
+    pub flags: u32,
+}
+
+impl Thresholds {
+    pub const DEFAULT: Thresholds =
+        Thresholds { low: 20, critical: 5, hysteresis: 3, flags: POLICY_DIM_ON_LOW | POLICY_SUSPEND_ON_CRITICAL };
+
+    pub fn is_valid(&self) -> bool {
+        self.critical < self.low
+            && self.low + self.hysteresis <= 100
+            && self.flags & !(POLICY_DIM_ON_LOW | POLICY_SUSPEND_ON_CRITICAL) == 0
+    }
+}
+
+/// What the service does after a reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Tell subscribers about a new level
+    Level(Level),
+    /// Tell subscribers the AC adapter was plugged in or out
+    Ac(bool),
+    /// Dim (true) or restore (false) the display
+    Dim(bool),
+    Suspend,
+}
+
+pub struct Policy {
+    thresholds: Thresholds,
+    level: Level,
+    ac_online: Option<bool>,
+    dimmed: bool,
+    /// Set once Critical triggered a suspend during this discharge
+    suspended: bool,
+}
+
+impl Policy {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self { thresholds, level: Level::Normal, ac_online: None, dimmed: false, suspended: false }
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    pub fn thresholds(&self) -> Thresholds {
+        self.thresholds
+    }
+
+    /// New thresholds apply from the next reading
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) -> bool {
+        if !thresholds.is_valid() {
+            return false;
+        }
+        self.thresholds = thresholds;
+        true
+    }
+
+    fn level_for(&self, reading: &Reading) -> Level {
+        if reading.ac_online || !reading.battery_present {
+            return Level::Normal;
+        }
+        let thresholds = self.thresholds;
+        if reading.state & STATE_CRITICAL != 0 || reading.percentage <= thresholds.critical {
+            return Level::Critical;
+        }
+        // Leaving a level needs the charge to climb past the hysteresis band
+        let low_exit = thresholds.low + thresholds.hysteresis;
+        let critical_exit = thresholds.critical + thresholds.hysteresis;
+        match self.level {
+            Level::Critical if reading.percentage <= critical_exit => Level::Critical,
+            Level::Critical | Level::Low if reading.percentage <= low_exit => Level::Low,
+            _ if reading.percentage <= thresholds.low => Level::Low,
+            _ => Level::Normal,
+        }
+    }
+
+    pub fn update(&mut self, reading: &Reading) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.ac_online != Some(reading.ac_online) {
+            if self.ac_online.is_some() {
+                actions.push(Action::Ac(reading.ac_online));
+            }
+            self.ac_online = Some(reading.ac_online);
+        }
+
+        let level = self.level_for(reading);
+        if level != self.level {
+            self.level = level;
+            actions.push(Action::Level(level));
+        }
+        if level < Level::Critical {
+            self.suspended = false;
+        }
+
+        let dim = level >= Level::Low && self.thresholds.flags & POLICY_DIM_ON_LOW != 0;
+        if dim != self.dimmed {
+            self.dimmed = dim;
+            actions.push(Action::Dim(dim));
+        }
+        if level == Level::Critical && !self.suspended && self.thresholds.flags & POLICY_SUSPEND_ON_CRITICAL != 0 {
+            self.suspended = true;
+            actions.push(Action::Suspend);
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on_battery(percentage: u32) -> Reading {
+        Reading { battery_present: true, state: STATE_DISCHARGING, percentage, ..Reading::default() }
+    }
+
+    #[test]
+    fn test_levels_follow_thresholds_with_hysteresis() {
+        let mut policy = Policy::new(Thresholds::DEFAULT);
+        assert!(policy.update(&on_battery(50)).is_empty());
+        assert_eq!(policy.update(&on_battery(20)), [Action::Level(Level::Low), Action::Dim(true)]);
+        // Wobbling inside the band changes nothing
+        assert!(policy.update(&on_battery(22)).is_empty());
+        assert!(policy.update(&on_battery(19)).is_empty());
+        assert_eq!(policy.update(&on_battery(24)), [Action::Level(Level::Normal), Action::Dim(false)]);
+
+        // Plugging in restores the display at once
+        policy.update(&on_battery(10));
+        let plugged = Reading { ac_online: true, ..on_battery(10) };
+        assert_eq!(policy.update(&plugged), [Action::Ac(true), Action::Level(Level::Normal), Action::Dim(false)]);
+    }
+
+    #[test]
+    fn test_critical_suspends_once_per_discharge() {
+        let mut policy = Policy::new(Thresholds::DEFAULT);
+        policy.update(&on_battery(30));
+        assert_eq!(policy.update(&on_battery(5)), [Action::Level(Level::Critical), Action::Dim(true), Action::Suspend]);
+        // Still critical after resuming: no second suspend
+        assert!(policy.update(&on_battery(4)).is_empty());
+        assert_eq!(policy.update(&on_battery(9)), [Action::Level(Level::Low)]);
+        assert_eq!(policy.update(&on_battery(3)), [Action::Level(Level::Critical), Action::Suspend]);
+
+        // The battery's alarm counts whatever the percentage
+        let mut policy = Policy::new(Thresholds { flags: 0, ..Thresholds::DEFAULT });
+        let alarm = Reading { state: STATE_DISCHARGING | STATE_CRITICAL, ..on_battery(40) };
+        assert_eq!(policy.update(&alarm), [Action::Level(Level::Critical)]);
+
+        let mut record = Vec::new();
+        alarm.encode(&mut record);
+        assert_eq!(record.len(), Reading::SIZE);
+        assert_eq!(Reading::decode(&record), Some(alarm));
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_thresholds() {
+        let mut policy = Policy::new(Thresholds::DEFAULT);
+        assert!(!policy.set_thresholds(Thresholds { low: 5, critical: 10, ..Thresholds::DEFAULT }));
+        assert!(!policy.set_thresholds(Thresholds { flags: 1 << 7, ..Thresholds::DEFAULT }));
+        assert!(policy.set_thresholds(Thresholds { low: 30, ..Thresholds::DEFAULT }));
+        assert_eq!(policy.thresholds().low, 30);
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Orion Operating System - Power Server Protocol
+ *
+ * IPC requests of the power policy server. All fields are little-endian;
+ * every message starts with a 32-bit opcode and every reply starts with a
+ * 32-bit signed status (0 or a negative errno).
+ *
+ *   STATUS       (none)                             -> level:u32 reading
+ *   GET_POLICY   (none)                             -> low:u32 critical:u32
+ *                                                      hysteresis:u32 flags:u32
+ *   SET_POLICY   low:u32 critical:u32 hysteresis:u32 flags:u32 -> (empty)
+ *   SUBSCRIBE    (none)                             -> (empty)
+ *   UNSUBSCRIBE  (none)                             -> (empty)
+ *
+ * `reading` is the record of the battery driver's READ reply (see
+ * drivers/char/src/acpi_battery.rs); thresholds are battery percentages.
+ * Subscribers receive LEVEL (level:u32 percentage:u32 time_to_empty:u32)
+ * whenever the level changes and AC (online:u32 percentage:u32) when the
+ * adapter is plugged in or out. Levels are 0 normal, 1 low, 2 critical.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use crate::policy::Thresholds;
+
+// Opcodes
+pub const OP_STATUS: u32 = 1;
+pub const OP_GET_POLICY: u32 = 2;
+pub const OP_SET_POLICY: u32 = 3;
+pub const OP_SUBSCRIBE: u32 = 4;
+pub const OP_UNSUBSCRIBE: u32 = 5;
+
+// Events sent to subscribers
+pub const EVENT_LEVEL: u32 = 0x8001;
+pub const EVENT_AC: u32 = 0x8002;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PowerRequest {
+    Status,
+    GetPolicy,
+    SetPolicy { thresholds: Thresholds },
+    Subscribe,
+    Unsubscribe,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl PowerRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_STATUS => Some(PowerRequest::Status),
+            OP_GET_POLICY => Some(PowerRequest::GetPolicy),
+            OP_SET_POLICY => Some(PowerRequest::SetPolicy {
+                thresholds: Thresholds {
+                    low: read_u32(data, 4)?,
+                    critical: read_u32(data, 8)?,
+                    hysteresis: read_u32(data, 12)?,
+                    flags: read_u32(data, 16)?,
+                },
+            }),
+            OP_SUBSCRIBE => Some(PowerRequest::Subscribe),
+            OP_UNSUBSCRIBE => Some(PowerRequest::Unsubscribe),
+            _ => None,
+        }
+    }
+}
+
+/// Message made of `opcode` followed by `fields`
+pub fn event(opcode: u32, fields: &[u32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + 4 * fields.len());
+    out.extend_from_slice(&opcode.to_le_bytes());
+    for field in fields {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_proto::reply;
+
+    #[test]
+    fn test_decodes_requests() {
+        let message = event(OP_SET_POLICY, &[25, 8, 2, 1]);
+        assert_eq!(
+            PowerRequest::decode(&message),
+            Some(PowerRequest::SetPolicy { thresholds: Thresholds { low: 25, critical: 8, hysteresis: 2, flags: 1 } })
+        );
+        assert_eq!(PowerRequest::decode(&message[..16]), None);
+        assert_eq!(PowerRequest::decode(&OP_SUBSCRIBE.to_le_bytes()), Some(PowerRequest::Subscribe));
+        assert_eq!(PowerRequest::decode(&9u32.to_le_bytes()), None);
+        assert_eq!(reply(STATUS_EIO, &[1])[..4], (-5i32).to_le_bytes());
+    }
+}
@@ -0,0 +1,180 @@
+/*
+ * Orion Operating System - Remote Shell Authorized Keys
+ *
+ * Ed25519 public keys allowed to log in, each with the rights it grants
+ * to the session: an interactive shell, command execution, and file
+ * upload and download. A client proves it holds the private key by
+ * signing the challenge of its connection together with the host key
+ * it saw, so a signature made for one server or one connection is
+ * worthless anywhere else.
+ *
+ * Both signatures also cover the binding of the connection: keying
+ * material exported from its TLS session under EXPORTER_LABEL (RFC 8446
+ * section 7.5), which each end computes on its own and nobody else
+ * knows. A man in the middle terminating TLS on both sides holds two
+ * sessions with two different bindings, so it can neither relay the
+ * host's HELLO nor the client's AUTH.
+ *
+ * A key's name is also the identity account its logins open a session
+ * for, so a key is useless until that account exists.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{self, PUBLIC_KEY_SIZE, SIGNATURE_SIZE};
+
+// Rights
+pub const RIGHT_SHELL: u32 = 1 << 0;
+pub const RIGHT_EXEC: u32 = 1 << 1;
+pub const RIGHT_UPLOAD: u32 = 1 << 2;
+pub const RIGHT_DOWNLOAD: u32 = 1 << 3;
+pub const RIGHT_ALL: u32 = (1 << 4) - 1;
+
+pub const MAX_KEYS: usize = 64;
+pub const MAX_KEY_NAME: usize = 64;
+
+/// Size of the per-connection challenge
+pub const CHALLENGE_SIZE: usize = 32;
+
+/// TLS exporter label and size of the channel binding
+pub const EXPORTER_LABEL: &str = "EXPORTER-orion-rsh";
+pub const BINDING_SIZE: usize = 32;
+
+/// Prefixes of the signed messages, so that neither signature can be
+/// replayed as the other
+pub const HOST_CONTEXT: &[u8] = b"orion-rsh host\0";
+pub const CLIENT_CONTEXT: &[u8] = b"orion-rsh client\0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyError {
+    Full,
+    Exists,
+    InvalidName,
+    InvalidRights,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizedKey {
+    pub id: u32,
+    pub name: String,
+    pub rights: u32,
+    pub public_key: [u8; PUBLIC_KEY_SIZE],
+}
+
+pub struct AuthorizedKeys {
+    keys: Vec<AuthorizedKey>,
+    next_id: u32,
+}
+
+/// Message the host signs for a connection
+pub fn host_message(challenge: &[u8; CHALLENGE_SIZE], binding: &[u8; BINDING_SIZE]) -> Vec<u8> {
+    let mut message = HOST_CONTEXT.to_vec();
+    message.extend_from_slice(challenge);
+    message.extend_from_slice(binding);
+    message
+}
+
+/// Message a client signs to log in
+pub fn client_message(
+    challenge: &[u8; CHALLENGE_SIZE],
+    host_key: &[u8; PUBLIC_KEY_SIZE],
+    binding: &[u8; BINDING_SIZE],
+) -> Vec<u8> {
+    let mut message = CLIENT_CONTEXT.to_vec();
+    message.extend_from_slice(challenge);
+    message.extend_from_slice(host_key);
+    message.extend_from_slice(binding);
+    message
+}
+
+impl AuthorizedKeys {
+    pub fn new() -> Self {
+        Self { keys: Vec::new(), next_id: 1 }
+    }
+
+    pub fn add(&mut self, name: &str, rights: u32, public_key: [u8; PUBLIC_KEY_SIZE]) -> Result<u32, KeyError> {
+        if name.is_empty() || name.len() > MAX_KEY_NAME || name.chars().any(|c| c.is_control()) {
+            return Err(KeyError::InvalidName);
+        }
+        if rights == 0 || rights & !RIGHT_ALL != 0 {
+            return Err(KeyError::InvalidRights);
+        }
+        if self.keys.iter().any(|key| key.public_key == public_key) {
+            return Err(KeyError::Exists);
+        }
+        if self.keys.len() >= MAX_KEYS {
+            return Err(KeyError::Full);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.keys.push(AuthorizedKey { id, name: String::from(name), rights, public_key });
+        Ok(id)
+    }
+
+    /// Remove a key; sessions it already opened keep running
+    pub fn remove(&mut self, id: u32) -> bool {
+        let before = self.keys.len();
+        self.keys.retain(|key| key.id != id);
+        self.keys.len() != before
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AuthorizedKey> {
+        self.keys.iter()
+    }
+
+    /// The key that signed `challenge`, `host_key` and `binding`, if it is
+    /// authorized
+    pub fn authenticate(
+        &self,
+        challenge: &[u8; CHALLENGE_SIZE],
+        host_key: &[u8; PUBLIC_KEY_SIZE],
+        binding: &[u8; BINDING_SIZE],
+        public_key: &[u8; PUBLIC_KEY_SIZE],
+        signature: &[u8; SIGNATURE_SIZE],
+    ) -> Option<&AuthorizedKey> {
+        let key = self.keys.iter().find(|key| key.public_key == *public_key)?;
+        ed25519::verify(public_key, &client_message(challenge, host_key, binding), signature).then_some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authenticates_signed_challenges() {
+        let seed = [0x42u8; 32];
+        let public_key = ed25519::public_key(&seed);
+        let host_key = ed25519::public_key(&[0x17u8; 32]);
+        let challenge = [9u8; CHALLENGE_SIZE];
+        let binding = [5u8; BINDING_SIZE];
+
+        let mut keys = AuthorizedKeys::new();
+        let id = keys.add("admin", RIGHT_SHELL | RIGHT_DOWNLOAD, public_key).unwrap();
+        assert_eq!(keys.add("again", RIGHT_SHELL, public_key), Err(KeyError::Exists));
+        assert_eq!(keys.add("", RIGHT_SHELL, [1; 32]), Err(KeyError::InvalidName));
+        assert_eq!(keys.add("none", 0, [1; 32]), Err(KeyError::InvalidRights));
+
+        let signature = ed25519::sign(&seed, &client_message(&challenge, &host_key, &binding));
+        let key = keys.authenticate(&challenge, &host_key, &binding, &public_key, &signature).unwrap();
+        assert_eq!((key.id, key.rights), (id, RIGHT_SHELL | RIGHT_DOWNLOAD));
+
+        // Bound to the connection, its TLS session and the host
+        assert!(keys.authenticate(&[8; CHALLENGE_SIZE], &host_key, &binding, &public_key, &signature).is_none());
+        assert!(keys.authenticate(&challenge, &host_key, &[6; BINDING_SIZE], &public_key, &signature).is_none());
+        assert!(keys.authenticate(&challenge, &public_key, &binding, &public_key, &signature).is_none());
+        let host_signature = ed25519::sign(&seed, &host_message(&challenge, &binding));
+        assert!(keys.authenticate(&challenge, &host_key, &binding, &public_key, &host_signature).is_none());
+
+        assert!(keys.remove(id));
+        assert!(keys.authenticate(&challenge, &host_key, &binding, &public_key, &signature).is_none());
+        assert!(!keys.remove(id));
+    }
+}
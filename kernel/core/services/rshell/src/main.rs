@@ -0,0 +1,548 @@
+/*
+ * Orion Operating System - Remote Shell Server
+ *
+ * Headless administration over the network: remote login, command
+ * execution and file transfer through a capability-authenticated
+ * protocol (see wire.rs) on a TLS socket of the network server. Clients
+ * log in with an Ed25519 key an administrator authorized, with the
+ * rights it carries (see keys.rs); the host proves itself with its own
+ * Ed25519 key, which never leaves the keyring. Each connection
+ * multiplexes channels: shells with a pseudo-terminal (see pty.rs),
+ * commands, uploads and downloads.
+ *
+ * A key logs into the account of the identity server named like it,
+ * which must exist and not be locked. Programs started for a channel
+ * are children of this server, attached to the login's identity session
+ * before they run, and reach their channel through its terminal
+ * requests (see protocol.rs); a program still running when its channel
+ * or connection closes is killed. Logins, accepted or not, are audited.
+ *
+ * The server is idle until an administrator sends START; the listener
+ * and the sessions are then polled between IPC checks. An accepted
+ * connection waits for its TLS handshake to end, which gives the binding
+ * both signatures of the login cover (see keys.rs), before its session
+ * starts. dev/tools/orion-rsh is a client.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_crypto::ed25519::SIGNATURE_SIZE;
+use orion_http::socket::{NetChannel, NetListener, NetStream};
+use orion_http::{IoError, Listener};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+use orion_sys::{
+    audit_emit, clock_get, close, kill, open, proc_info, read, resume, spawn_suspended, wait, write, ProcInfo, O_CREAT,
+    O_RDONLY, O_TRUNC, O_WRONLY,
+};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the sessions and their channel buffers
+const HEAP_SIZE: usize = 4 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod keys;
+mod protocol;
+mod pty;
+mod session;
+mod wire;
+
+use keys::{AuthorizedKeys, KeyError, BINDING_SIZE, CHALLENGE_SIZE, EXPORTER_LABEL};
+use protocol::*;
+use session::{Identity, Login, Session, SessionStats, ShellHost, AUTH_TIMEOUT_NS};
+
+/// Pause between two polls of the sessions
+const POLL_INTERVAL_NS: u64 = 5_000_000;
+
+const LISTEN_BACKLOG: u16 = 8;
+const MAX_SESSIONS: usize = 16;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+// Audit record types
+const AUDIT_RSH_LOGIN: u32 = 0x1601;
+const AUDIT_RSH_REJECTED: u32 = 0x1602;
+
+/// Entropy GET_RANDOM request opcode (see services/entropy/src/protocol.rs)
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+
+/// Keyring SIGN request opcode (see services/keyring/src/protocol.rs)
+const KEYRING_OP_SIGN: u32 = 9;
+
+// Identity front end requests (see services/identity/src/protocol.rs)
+const IDENTITY_OP_OPEN_SESSION: u32 = 0x11;
+const IDENTITY_OP_ATTACH: u32 = 0x12;
+const IDENTITY_OP_LOGOUT: u32 = 0x13;
+
+/// Where identity sessions of the server come from
+const SESSION_SOURCE: &str = "rsh";
+
+/// Process states from which a program no longer runs (see orion-ps)
+const PROC_STATE_EXIT: u32 = 4;
+
+/// Exit code reported for a program reaped by someone else
+const EXIT_UNKNOWN: i32 = -1;
+
+/// IPC channel to the network server used by the listener
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Programs and files of the sessions, through the kernel, the fs
+/// server and the identity server
+struct System {
+    /// Programs killed with their channel, to reap once they are gone
+    killed: Vec<u64>,
+    identity: IpcChannel,
+}
+
+impl System {
+    fn new() -> Self {
+        Self { killed: Vec::new(), identity: IpcChannel::connect("identity") }
+    }
+
+    /// Send an identity request; the reply payload of a successful call
+    fn identity_call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.identity.call(request).map_err(|_| STATUS_EIO)?;
+        if response.len() < 4 {
+            return Err(STATUS_EIO);
+        }
+        match i32::from_le_bytes([response[0], response[1], response[2], response[3]]) {
+            STATUS_OK => Ok(response[4..].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn read_image(path: &str) -> Result<Vec<u8>, i32> {
+        let fd = open(path, O_RDONLY)?;
+        let mut image = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let result = loop {
+            match read(fd, &mut chunk) {
+                Ok(0) => break Ok(image),
+                Ok(count) => image.extend_from_slice(&chunk[..count]),
+                Err(status) => break Err(status),
+            }
+        };
+        let _ = close(fd);
+        result
+    }
+
+    /// Exit code of `pid` once it no longer runs
+    fn exit_code(pid: u64) -> Option<i32> {
+        let mut info = ProcInfo::default();
+        match proc_info(pid.saturating_sub(1), &mut info) {
+            Ok(found) if found == pid && info.state < PROC_STATE_EXIT => None,
+            // Exited, or already reaped
+            _ => Some(wait(pid).unwrap_or(EXIT_UNKNOWN)),
+        }
+    }
+}
+
+impl ShellHost for System {
+    fn open_session(&mut self, user: &str) -> Result<u64, i32> {
+        let mut request = IDENTITY_OP_OPEN_SESSION.to_le_bytes().to_vec();
+        for field in [user, SESSION_SOURCE] {
+            request.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            request.extend_from_slice(field.as_bytes());
+        }
+        let login = self.identity_call(&request)?;
+        login.get(..8).and_then(|session| session.try_into().ok()).map(u64::from_le_bytes).ok_or(STATUS_EIO)
+    }
+
+    fn close_session(&mut self, session: u64) {
+        let mut request = IDENTITY_OP_LOGOUT.to_le_bytes().to_vec();
+        request.extend_from_slice(&session.to_le_bytes());
+        let _ = self.identity_call(&request);
+    }
+
+    fn spawn(&mut self, command: &str, session: u64) -> Result<u64, i32> {
+        let path = command.split_whitespace().next().ok_or(STATUS_EINVAL)?;
+        let image = Self::read_image(path)?;
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let pid = spawn_suspended(name, &image)?;
+
+        // The program gets the rights of the account before it runs
+        let mut request = IDENTITY_OP_ATTACH.to_le_bytes().to_vec();
+        request.extend_from_slice(&session.to_le_bytes());
+        request.extend_from_slice(&pid.to_le_bytes());
+        if let Err(status) = self.identity_call(&request).and_then(|_| resume(pid)) {
+            let _ = kill(pid);
+            return Err(status);
+        }
+        Ok(pid)
+    }
+
+    fn kill(&mut self, pid: u64) {
+        if kill(pid).is_ok() {
+            self.killed.push(pid);
+        }
+    }
+
+    fn open_file(&mut self, path: &str, write: bool) -> Result<u64, i32> {
+        let flags = if write { O_WRONLY | O_CREAT | O_TRUNC } else { O_RDONLY };
+        open(path, flags)
+    }
+
+    fn read_file(&mut self, file: u64, buffer: &mut [u8]) -> Result<usize, i32> {
+        read(file, buffer)
+    }
+
+    fn write_file(&mut self, file: u64, data: &[u8]) -> Result<usize, i32> {
+        write(file, data)
+    }
+
+    fn close_file(&mut self, file: u64) {
+        let _ = close(file);
+    }
+}
+
+struct Running {
+    listener: NetListener<NetIpc>,
+    config: StartConfig,
+}
+
+struct RshServer {
+    running: Option<Running>,
+    sessions: Vec<Session<NetStream<NetIpc>>>,
+    /// Accepted connections still in their TLS handshake, with the time
+    /// they were accepted
+    handshaking: Vec<(NetStream<NetIpc>, u64)>,
+    keys: AuthorizedKeys,
+    system: System,
+    /// Counters of sessions already closed
+    closed: SessionStats,
+    logins: u64,
+    rejected: u64,
+    entropy: IpcChannel,
+    keyring: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl RshServer {
+    fn new() -> Self {
+        Self {
+            running: None,
+            sessions: Vec::new(),
+            handshaking: Vec::new(),
+            keys: AuthorizedKeys::new(),
+            system: System::new(),
+            closed: SessionStats::default(),
+            logins: 0,
+            rejected: 0,
+            entropy: IpcChannel::connect("entropy"),
+            keyring: IpcChannel::connect("keyring"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            if self.running.is_some() {
+                self.poll_sessions();
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    /// Draw the challenge of a connection from the entropy service
+    fn random_challenge(&mut self) -> Option<[u8; CHALLENGE_SIZE]> {
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+        request.extend_from_slice(&(CHALLENGE_SIZE as u32).to_le_bytes());
+        request.extend_from_slice(&0u32.to_le_bytes());
+
+        let response = self.entropy.call(&request).ok()?;
+        if response.len() < 4 + CHALLENGE_SIZE || response[..4] != STATUS_OK.to_le_bytes() {
+            return None;
+        }
+        let mut challenge = [0u8; CHALLENGE_SIZE];
+        challenge.copy_from_slice(&response[4..4 + CHALLENGE_SIZE]);
+        Some(challenge)
+    }
+
+    /// Sign `message` with the host key held by the keyring
+    fn host_signature(&mut self, host_key_handle: u64, message: &[u8]) -> Option<[u8; SIGNATURE_SIZE]> {
+        let mut request = Vec::with_capacity(12 + message.len());
+        request.extend_from_slice(&KEYRING_OP_SIGN.to_le_bytes());
+        request.extend_from_slice(&host_key_handle.to_le_bytes());
+        request.extend_from_slice(message);
+
+        let response = self.keyring.call(&request).ok()?;
+        if response.len() < 4 + SIGNATURE_SIZE || response[..4] != STATUS_OK.to_le_bytes() {
+            return None;
+        }
+        response[4..4 + SIGNATURE_SIZE].try_into().ok()
+    }
+
+    /// Challenge of a new connection, signed by the host with the binding
+    /// of its TLS session
+    fn identity(&mut self, binding: [u8; BINDING_SIZE]) -> Option<Identity> {
+        let (host_key, host_key_handle) = {
+            let config = &self.running.as_ref()?.config;
+            (config.host_public_key, config.host_key_handle)
+        };
+        let challenge = self.random_challenge()?;
+        let signature = self.host_signature(host_key_handle, &keys::host_message(&challenge, &binding))?;
+        Some(Identity { host_key, challenge, binding, signature })
+    }
+
+    fn start(&mut self, config: StartConfig) -> i32 {
+        if self.running.is_some() {
+            return STATUS_EBUSY;
+        }
+        let listener = match NetListener::bind(NetIpc(IpcChannel::connect("net")), 0, config.port, LISTEN_BACKLOG) {
+            Ok(listener) => listener,
+            Err(status) => return status,
+        };
+        // Dropping the listener closes the socket if TLS cannot be set up
+        if let Err(status) = listener.enable_tls(config.certificate_handle, config.key_handle) {
+            return status;
+        }
+        self.running = Some(Running { listener, config });
+
+        // A host key the keyring cannot sign with would turn every client away
+        if self.identity([0; BINDING_SIZE]).is_none() {
+            self.running = None;
+            return STATUS_EPERM;
+        }
+        STATUS_OK
+    }
+
+    fn stop(&mut self) -> i32 {
+        if self.running.take().is_none() {
+            return STATUS_ENOENT;
+        }
+        self.handshaking.clear();
+        for mut session in self.sessions.drain(..) {
+            session.close(&mut self.system);
+            self.closed.bytes_received += session.stats.bytes_received;
+            self.closed.bytes_sent += session.stats.bytes_sent;
+        }
+        STATUS_OK
+    }
+
+    fn audit(&mut self, login: Login) {
+        let (kind, record) = match login {
+            Login::Accepted { key, name } => {
+                self.logins += 1;
+                (AUDIT_RSH_LOGIN, format!("rsh-login key={} name=\"{}\"", key, name))
+            }
+            Login::Rejected => {
+                self.rejected += 1;
+                (AUDIT_RSH_REJECTED, String::from("rsh-rejected"))
+            }
+        };
+        let _ = audit_emit(kind, record.as_bytes());
+    }
+
+    fn poll_sessions(&mut self) {
+        loop {
+            let Some(running) = self.running.as_mut() else {
+                return;
+            };
+            let stream = match running.listener.accept() {
+                Ok(stream) => stream,
+                Err(IoError::WouldBlock) | Err(IoError::Closed) => break,
+            };
+            if self.sessions.len() + self.handshaking.len() >= MAX_SESSIONS {
+                // Dropping the stream closes the connection
+                continue;
+            }
+            self.handshaking.push((stream, monotonic_ns()));
+        }
+
+        // A session starts once the TLS session gives its binding; dropping
+        // a stream whose handshake failed or took too long closes it
+        let now = monotonic_ns();
+        let mut index = 0;
+        while index < self.handshaking.len() {
+            let (stream, accepted) = &self.handshaking[index];
+            let binding = stream
+                .tls_exporter(EXPORTER_LABEL, BINDING_SIZE as u16)
+                .and_then(|binding| <[u8; BINDING_SIZE]>::try_from(binding).map_err(|_| STATUS_EIO));
+            match binding {
+                Err(STATUS_EAGAIN) if now.saturating_sub(*accepted) <= AUTH_TIMEOUT_NS => index += 1,
+                Ok(binding) => {
+                    let (stream, accepted) = self.handshaking.swap_remove(index);
+                    let shell = self.running.as_ref().map(|running| running.config.shell.clone()).unwrap_or_default();
+                    if let Some(identity) = self.identity(binding) {
+                        self.sessions.push(Session::new(stream, identity, &shell, accepted));
+                    }
+                }
+                Err(_) => {
+                    self.handshaking.swap_remove(index);
+                }
+            }
+        }
+
+        let mut logins = Vec::new();
+        for session in self.sessions.iter_mut() {
+            logins.extend(session.poll(&self.keys, &mut self.system, now));
+            for pid in session.pids() {
+                if let Some(code) = System::exit_code(pid) {
+                    session.exited(pid, code, &mut self.system);
+                }
+            }
+        }
+        for login in logins {
+            self.audit(login);
+        }
+        self.system.killed.retain(|&pid| System::exit_code(pid).is_none());
+
+        let closed = &mut self.closed;
+        self.sessions.retain(|session| {
+            if session.is_closed() {
+                closed.bytes_received += session.stats.bytes_received;
+                closed.bytes_sent += session.stats.bytes_sent;
+            }
+            !session.is_closed()
+        });
+    }
+
+    /// Serve a terminal request of the program `sender`
+    fn serve_terminal(&mut self, sender: u64, request: RshRequest, out: &mut Vec<u8>) -> i32 {
+        let Some(session) = self.sessions.iter_mut().find(|session| session.pids().contains(&sender)) else {
+            return STATUS_EPERM;
+        };
+        let result = match request {
+            RshRequest::Attach => match session.attach(sender) {
+                Some(attachment) => {
+                    encode_attachment(&attachment, out);
+                    Ok(())
+                }
+                None => Err(STATUS_EPERM),
+            },
+            RshRequest::Read { max } => {
+                session.terminal_read(sender, max as usize).map(|data| out.extend_from_slice(&data))
+            }
+            RshRequest::Write { data } => session
+                .terminal_write(sender, &data, &mut self.system)
+                .map(|written| out.extend_from_slice(&(written as u32).to_le_bytes())),
+            RshRequest::Mode { mode } => session.set_mode(sender, mode),
+            _ => Err(STATUS_EINVAL),
+        };
+        result.map_or_else(|status| status, |_| STATUS_OK)
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match RshRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let mut payload = Vec::new();
+        if request.is_terminal() {
+            let status = self.serve_terminal(message.sender, request, &mut payload);
+            self.ipc_channel.send(message.sender, &reply(status, &payload));
+            return;
+        }
+
+        let rights = match request {
+            RshRequest::Status => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let status = match request {
+            RshRequest::Start(config) => self.start(config),
+            RshRequest::Stop => self.stop(),
+            RshRequest::Status => {
+                let (mut received, mut sent) = (self.closed.bytes_received, self.closed.bytes_sent);
+                let mut channels = 0;
+                for session in self.sessions.iter() {
+                    received += session.stats.bytes_received;
+                    sent += session.stats.bytes_sent;
+                    channels += session.channel_count();
+                }
+                for value in [self.sessions.len() as u32, channels as u32, self.keys.iter().count() as u32] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                for value in [self.logins, self.rejected, received, sent] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                STATUS_OK
+            }
+            RshRequest::AddKey { rights, public_key, name } => match self.keys.add(&name, rights, public_key) {
+                Ok(id) => {
+                    payload.extend_from_slice(&id.to_le_bytes());
+                    STATUS_OK
+                }
+                Err(KeyError::Full) => STATUS_ENOSPC,
+                Err(KeyError::Exists) => STATUS_EEXIST,
+                Err(KeyError::InvalidName | KeyError::InvalidRights) => STATUS_EINVAL,
+            },
+            RshRequest::RemoveKey { id } => {
+                if self.keys.remove(id) {
+                    STATUS_OK
+                } else {
+                    STATUS_ENOENT
+                }
+            }
+            RshRequest::ListKeys => {
+                for key in self.keys.iter() {
+                    encode_key(key, &mut payload);
+                }
+                STATUS_OK
+            }
+            // Terminal requests were served above
+            _ => STATUS_EINVAL,
+        };
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = RshServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,263 @@
+/*
+ * Orion Operating System - Remote Shell Server Protocol
+ *
+ * IPC requests of the remote shell server. All fields are little-endian;
+ * every message starts with a 32-bit opcode and every reply starts with
+ * a 32-bit signed status (0 or a negative errno).
+ *
+ * Administration:
+ *
+ *   START       port:u16 cert:u64 key:u64 host_key:u64
+ *               host_public[32] shell          -> (empty)
+ *   STOP        (none)                         -> (empty)
+ *   STATUS      (none)                         -> sessions:u32 channels:u32
+ *                                                 keys:u32 logins:u64
+ *                                                 rejected:u64 bytes_in:u64
+ *                                                 bytes_out:u64
+ *   ADD_KEY     rights:u32 public_key[32] name -> id:u32
+ *   REMOVE_KEY  id:u32                         -> (empty)
+ *   LIST_KEYS   (none)                         -> { id:u32 rights:u32
+ *                                                   public_key[32] name }*
+ *
+ * START listens on `port` (0 for 2022) with TLS terminated by the network
+ * server from the keyring-held `cert` and `key`; there is no plain text
+ * mode. `host_key` is the keyring handle of the Ed25519 host key that
+ * signs the challenge of every connection and `host_public` its public
+ * half; `shell` is the program of shell channels that name no command.
+ * STATUS needs CAP_READ, the others CAP_ADMIN. Rights of a key are the
+ * RIGHT_* bits of keys.rs.
+ *
+ * Programs started for a shell or exec channel reach it with the
+ * terminal requests, which are only served to them:
+ *
+ *   TERM_ATTACH (none)          -> mode:u32 columns:u16 rows:u16
+ *                                  rights:u32 command user
+ *   TERM_READ   max:u32         -> data
+ *   TERM_WRITE  data...         -> written:u32
+ *   TERM_MODE   mode:u32        -> (empty)
+ *
+ * TERM_ATTACH reports the line discipline mode (MODE_NONE without a
+ * terminal), the window size, the command line the program was started
+ * for and the key that logged in. TERM_READ answers EAGAIN while no input
+ * is waiting and an empty read at the end of it; TERM_WRITE answers
+ * EAGAIN while the client is behind. TERM_MODE sets the line discipline
+ * of a shell channel (MODE_* of pty.rs). A program's exit ends its
+ * channel, with its exit code.
+ *
+ * Strings are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::PUBLIC_KEY_SIZE;
+
+use crate::keys::{AuthorizedKey, MAX_KEY_NAME};
+use crate::session::Attachment;
+
+// Opcodes
+pub const OP_START: u32 = 1;
+pub const OP_STOP: u32 = 2;
+pub const OP_STATUS: u32 = 3;
+pub const OP_ADD_KEY: u32 = 4;
+pub const OP_REMOVE_KEY: u32 = 5;
+pub const OP_LIST_KEYS: u32 = 6;
+pub const OP_TERM_ATTACH: u32 = 0x20;
+pub const OP_TERM_READ: u32 = 0x21;
+pub const OP_TERM_WRITE: u32 = 0x22;
+pub const OP_TERM_MODE: u32 = 0x23;
+
+pub const DEFAULT_PORT: u16 = 2022;
+
+/// TERM_ATTACH mode of a channel without a terminal
+pub const MODE_NONE: u32 = u32::MAX;
+
+/// Longest login shell path
+pub const MAX_SHELL: usize = 256;
+
+/// Largest TERM_READ, bounded by what one IPC reply carries
+pub const MAX_TERM_READ: u32 = 4096;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EAGAIN: i32 = -11;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EEXIST: i32 = -17;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StartConfig {
+    pub port: u16,
+    pub certificate_handle: u64,
+    pub key_handle: u64,
+    pub host_key_handle: u64,
+    pub host_public_key: [u8; PUBLIC_KEY_SIZE],
+    pub shell: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RshRequest {
+    Start(StartConfig),
+    Stop,
+    Status,
+    AddKey { rights: u32, public_key: [u8; PUBLIC_KEY_SIZE], name: String },
+    RemoveKey { id: u32 },
+    ListKeys,
+    Attach,
+    Read { max: u32 },
+    Write { data: Vec<u8> },
+    Mode { mode: u32 },
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, utf-8 bytes` field
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some(String::from(core::str::from_utf8(bytes).ok()?))
+}
+
+fn write_string(text: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+impl RshRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_START => {
+                let shell = read_string(data, 62)?;
+                if shell.is_empty() || shell.len() > MAX_SHELL {
+                    return None;
+                }
+                let port = match read_u16(data, 4)? {
+                    0 => DEFAULT_PORT,
+                    port => port,
+                };
+                Some(RshRequest::Start(StartConfig {
+                    port,
+                    certificate_handle: read_u64(data, 6)?,
+                    key_handle: read_u64(data, 14)?,
+                    host_key_handle: read_u64(data, 22)?,
+                    host_public_key: data.get(30..62)?.try_into().ok()?,
+                    shell,
+                }))
+            }
+            OP_STOP => Some(RshRequest::Stop),
+            OP_STATUS => Some(RshRequest::Status),
+            OP_ADD_KEY => {
+                let name = read_string(data, 40)?;
+                if name.len() > MAX_KEY_NAME {
+                    return None;
+                }
+                Some(RshRequest::AddKey {
+                    rights: read_u32(data, 4)?,
+                    public_key: data.get(8..40)?.try_into().ok()?,
+                    name,
+                })
+            }
+            OP_REMOVE_KEY => Some(RshRequest::RemoveKey { id: read_u32(data, 4)? }),
+            OP_LIST_KEYS => Some(RshRequest::ListKeys),
+            OP_TERM_ATTACH => Some(RshRequest::Attach),
+            OP_TERM_READ => Some(RshRequest::Read { max: read_u32(data, 4)?.min(MAX_TERM_READ) }),
+            OP_TERM_WRITE => Some(RshRequest::Write { data: data.get(4..)?.to_vec() }),
+            OP_TERM_MODE => Some(RshRequest::Mode { mode: read_u32(data, 4)? }),
+            _ => None,
+        }
+    }
+
+    /// Whether the request comes from a program of a channel rather than
+    /// an administrator
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            RshRequest::Attach | RshRequest::Read { .. } | RshRequest::Write { .. } | RshRequest::Mode { .. }
+        )
+    }
+}
+
+/// Append a LIST_KEYS record
+pub fn encode_key(key: &AuthorizedKey, out: &mut Vec<u8>) {
+    out.extend_from_slice(&key.id.to_le_bytes());
+    out.extend_from_slice(&key.rights.to_le_bytes());
+    out.extend_from_slice(&key.public_key);
+    write_string(&key.name, out);
+}
+
+/// Append a TERM_ATTACH reply
+pub fn encode_attachment(attachment: &Attachment, out: &mut Vec<u8>) {
+    out.extend_from_slice(&attachment.mode.unwrap_or(MODE_NONE).to_le_bytes());
+    out.extend_from_slice(&attachment.size.columns.to_le_bytes());
+    out.extend_from_slice(&attachment.size.rows.to_le_bytes());
+    out.extend_from_slice(&attachment.rights.to_le_bytes());
+    write_string(&attachment.command, out);
+    write_string(&attachment.user, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_start_and_keys() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&OP_START.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        for handle in [7u64, 8, 9] {
+            data.extend_from_slice(&handle.to_le_bytes());
+        }
+        data.extend_from_slice(&[0xab; 32]);
+        write_string("/bin/osh", &mut data);
+
+        let expected = StartConfig {
+            port: DEFAULT_PORT,
+            certificate_handle: 7,
+            key_handle: 8,
+            host_key_handle: 9,
+            host_public_key: [0xab; 32],
+            shell: String::from("/bin/osh"),
+        };
+        assert_eq!(RshRequest::decode(&data), Some(RshRequest::Start(expected)));
+        assert_eq!(RshRequest::decode(&data[..data.len() - 1]), None);
+
+        let mut add = OP_ADD_KEY.to_le_bytes().to_vec();
+        add.extend_from_slice(&3u32.to_le_bytes());
+        add.extend_from_slice(&[1; 32]);
+        write_string("ops", &mut add);
+        let request = RshRequest::decode(&add).unwrap();
+        assert_eq!(request, RshRequest::AddKey { rights: 3, public_key: [1; 32], name: String::from("ops") });
+        assert!(!request.is_terminal());
+
+        let mut read = OP_TERM_READ.to_le_bytes().to_vec();
+        read.extend_from_slice(&u32::MAX.to_le_bytes());
+        let request = RshRequest::decode(&read).unwrap();
+        assert_eq!(request, RshRequest::Read { max: MAX_TERM_READ });
+        assert!(request.is_terminal());
+    }
+}
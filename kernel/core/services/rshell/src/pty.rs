@@ -0,0 +1,212 @@
+/*
+ * Orion Operating System - Remote Shell Terminals
+ *
+ * Line discipline of the pseudo-terminal behind a shell channel. Keys
+ * typed by the client arrive as channel data and pass through it before
+ * the program reads them: in canonical mode they are edited a line at a
+ * time (erase, kill, end of file) and echoed back, and Ctrl-C interrupts
+ * the program; in raw mode every byte goes through untouched. Output of
+ * the program has its newlines turned into CR LF, as the client's
+ * terminal expects.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+// Modes, as set by the program with TERM_MODE
+pub const MODE_CANONICAL: u32 = 1 << 0;
+pub const MODE_ECHO: u32 = 1 << 1;
+pub const MODE_SIGNALS: u32 = 1 << 2;
+pub const MODE_DEFAULT: u32 = MODE_CANONICAL | MODE_ECHO | MODE_SIGNALS;
+pub const MODE_ALL: u32 = MODE_DEFAULT;
+
+// Control characters
+const INTR: u8 = 0x03;
+const EOF: u8 = 0x04;
+const BACKSPACE: u8 = 0x08;
+const KILL: u8 = 0x15;
+const ERASE: u8 = 0x7f;
+
+/// Input waiting for the program above which typed keys are dropped
+pub const MAX_PENDING_INPUT: usize = 4096;
+
+/// What a key asks of the session beyond the input itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Ctrl-C: the program is killed
+    Interrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSize {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+pub struct LineDiscipline {
+    pub mode: u32,
+    pub size: WindowSize,
+    /// Line being edited in canonical mode
+    line: Vec<u8>,
+    /// Input the program can read
+    ready: Vec<u8>,
+    /// End of file typed at the start of a line, returned once
+    eof: bool,
+}
+
+impl LineDiscipline {
+    pub fn new(size: WindowSize) -> Self {
+        Self { mode: MODE_DEFAULT, size, line: Vec::new(), ready: Vec::new(), eof: false }
+    }
+
+    /// Whether the program has anything to read, end of file included
+    pub fn readable(&self) -> bool {
+        !self.ready.is_empty() || self.eof
+    }
+
+    /// Switch modes; a line being edited is handed over as it is when
+    /// canonical mode ends
+    pub fn set_mode(&mut self, mode: u32) {
+        if mode & MODE_CANONICAL == 0 {
+            self.ready.append(&mut self.line);
+        }
+        self.mode = mode & MODE_ALL;
+    }
+
+    /// Pass keys typed by the client through, appending what must be
+    /// echoed to `echo`
+    pub fn input(&mut self, keys: &[u8], echo: &mut Vec<u8>) -> Option<Signal> {
+        let mut signal = None;
+        for &key in keys {
+            if self.mode & MODE_SIGNALS != 0 && key == INTR {
+                self.line.clear();
+                if self.mode & MODE_ECHO != 0 {
+                    echo.extend_from_slice(b"^C\r\n");
+                }
+                signal = Some(Signal::Interrupt);
+                continue;
+            }
+            if self.mode & MODE_CANONICAL == 0 {
+                if self.ready.len() < MAX_PENDING_INPUT {
+                    self.ready.push(key);
+                    if self.mode & MODE_ECHO != 0 {
+                        echo.push(key);
+                    }
+                }
+                continue;
+            }
+            match key {
+                ERASE | BACKSPACE => {
+                    if self.line.pop().is_some() && self.mode & MODE_ECHO != 0 {
+                        echo.extend_from_slice(b"\x08 \x08");
+                    }
+                }
+                KILL => {
+                    if self.mode & MODE_ECHO != 0 {
+                        for _ in 0..self.line.len() {
+                            echo.extend_from_slice(b"\x08 \x08");
+                        }
+                    }
+                    self.line.clear();
+                }
+                // An empty line ends the input; otherwise the line goes out
+                // without a newline
+                EOF => {
+                    if self.line.is_empty() {
+                        self.eof = true;
+                    } else {
+                        self.ready.append(&mut self.line);
+                    }
+                }
+                b'\r' | b'\n' => {
+                    self.line.push(b'\n');
+                    if self.mode & MODE_ECHO != 0 {
+                        echo.extend_from_slice(b"\r\n");
+                    }
+                    if self.ready.len() + self.line.len() <= MAX_PENDING_INPUT {
+                        self.ready.append(&mut self.line);
+                    } else {
+                        self.line.clear();
+                    }
+                }
+                _ => {
+                    if self.line.len() < MAX_PENDING_INPUT {
+                        self.line.push(key);
+                        if self.mode & MODE_ECHO != 0 {
+                            echo.push(key);
+                        }
+                    }
+                }
+            }
+        }
+        signal
+    }
+
+    /// Take up to `max` bytes of input for the program; an empty read
+    /// after `readable` is the end of file
+    pub fn read(&mut self, max: usize) -> Vec<u8> {
+        if self.ready.is_empty() {
+            self.eof = false;
+            return Vec::new();
+        }
+        let count = max.min(self.ready.len());
+        self.ready.drain(..count).collect()
+    }
+
+    /// Program output as the client's terminal shows it
+    pub fn output(&self, data: &[u8], out: &mut Vec<u8>) {
+        for &byte in data {
+            if byte == b'\n' {
+                out.push(b'\r');
+            }
+            out.push(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edits_lines_and_interrupts() {
+        let mut pty = LineDiscipline::new(WindowSize { columns: 80, rows: 24 });
+        let mut echo = Vec::new();
+
+        // Nothing is readable before the line is complete
+        assert_eq!(pty.input(b"lx\x7fs", &mut echo), None);
+        assert!(!pty.readable());
+        assert_eq!(pty.input(b" -l\r", &mut echo), None);
+        assert_eq!(echo, b"lx\x08 \x08s -l\r\n");
+        assert_eq!(pty.read(3), b"ls ");
+        assert_eq!(pty.read(64), b"-l\n");
+
+        echo.clear();
+        assert_eq!(pty.input(b"rm -rf\x15\x03", &mut echo), Some(Signal::Interrupt));
+        assert!(!pty.readable());
+        assert!(echo.ends_with(b"^C\r\n"));
+
+        // End of file at the start of a line reads as empty once
+        pty.input(b"\x04", &mut echo);
+        assert!(pty.readable());
+        assert!(pty.read(64).is_empty());
+        assert!(!pty.readable());
+
+        // Raw mode passes everything through unechoed
+        pty.input(b"vi", &mut echo);
+        pty.set_mode(0);
+        echo.clear();
+        pty.input(b"\x1b:q\r\x03", &mut echo);
+        assert!(echo.is_empty());
+        assert_eq!(pty.read(64), b"vi\x1b:q\r\x03");
+
+        let mut out = Vec::new();
+        pty.output(b"a\nb\n", &mut out);
+        assert_eq!(out, b"a\r\nb\r\n");
+    }
+}
@@ -0,0 +1,738 @@
+/*
+ * Orion Operating System - Remote Shell Sessions
+ *
+ * One client connection: the HELLO/AUTH handshake, then the channels
+ * the client opens (see wire.rs). A shell or exec channel runs a program
+ * that reaches its channel through the terminal requests of the server
+ * (see protocol.rs); what the client sends waits in the channel until
+ * the program reads it, through the line discipline of pty.rs for a
+ * shell. Uploads and downloads move a file through the fs server.
+ *
+ * A login opens a session of the identity server for the account named
+ * like the key; the programs of the channels run attached to it, with
+ * what the account's groups grant, and it is logged out with the
+ * connection.
+ *
+ * Sessions are polled like the RFB sessions of the VNC server: input is
+ * buffered until a whole frame is there, and a frame that cannot be
+ * taken yet (input for a program that has not read the previous one)
+ * stays in the buffer, which stops reading from the connection and lets
+ * TCP push back on the client. Output stops being produced, by programs
+ * and downloads alike, while the queue is above OUTPUT_HIGH_WATER.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{PUBLIC_KEY_SIZE, SIGNATURE_SIZE};
+use orion_http::{IoError, Transport};
+
+use crate::keys::{
+    AuthorizedKeys, BINDING_SIZE, CHALLENGE_SIZE, RIGHT_DOWNLOAD, RIGHT_EXEC, RIGHT_SHELL, RIGHT_UPLOAD,
+};
+use crate::protocol::{STATUS_EAGAIN, STATUS_EEXIST, STATUS_EINVAL, STATUS_ENOSPC, STATUS_EPERM, STATUS_OK};
+use crate::pty::{LineDiscipline, Signal, WindowSize, MAX_PENDING_INPUT};
+use crate::wire::{self, ChannelKind, ClientFrame, MAX_PAYLOAD};
+
+/// Channels open at once on a connection
+pub const MAX_CHANNELS: usize = 8;
+
+/// Queued output above which programs and downloads are held back
+pub const OUTPUT_HIGH_WATER: usize = 256 * 1024;
+
+/// Time a client has to log in
+pub const AUTH_TIMEOUT_NS: u64 = 30_000_000_000;
+
+/// Bytes requested from the transport per read
+const READ_SIZE: usize = 4096;
+
+/// Who the server is to a new connection
+#[derive(Debug, Clone, Copy)]
+pub struct Identity {
+    pub host_key: [u8; PUBLIC_KEY_SIZE],
+    pub challenge: [u8; CHALLENGE_SIZE],
+    /// Keying material exported from the TLS session of the connection
+    pub binding: [u8; BINDING_SIZE],
+    /// Host key signature of the challenge and binding (see keys::host_message)
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+/// Effects of a session on the rest of the system
+pub trait ShellHost {
+    /// Open an identity session for the account `user`
+    fn open_session(&mut self, user: &str) -> Result<u64, i32>;
+    fn close_session(&mut self, session: u64);
+    /// Start the program named by the first word of `command` in
+    /// `session`; its pid
+    fn spawn(&mut self, command: &str, session: u64) -> Result<u64, i32>;
+    fn kill(&mut self, pid: u64);
+    fn open_file(&mut self, path: &str, write: bool) -> Result<u64, i32>;
+    fn read_file(&mut self, file: u64, buffer: &mut [u8]) -> Result<usize, i32>;
+    fn write_file(&mut self, file: u64, data: &[u8]) -> Result<usize, i32>;
+    fn close_file(&mut self, file: u64);
+}
+
+/// Outcome of a login attempt, for the server to audit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Login {
+    Accepted { key: u32, name: String },
+    Rejected,
+}
+
+/// What a program learns about its channel when it attaches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// Line discipline mode, None without a terminal
+    pub mode: Option<u32>,
+    pub size: WindowSize,
+    pub command: String,
+    pub user: String,
+    pub rights: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionStats {
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Auth,
+    Open,
+    /// Waiting for queued output to drain before closing
+    Closing,
+    Closed,
+}
+
+enum Endpoint {
+    Process {
+        pid: u64,
+        pty: Option<LineDiscipline>,
+        /// Standard input of a program without a terminal
+        input: Vec<u8>,
+        /// The client sent EOF
+        input_closed: bool,
+    },
+    Upload {
+        file: u64,
+    },
+    Download {
+        file: u64,
+    },
+}
+
+struct Channel {
+    id: u32,
+    command: String,
+    endpoint: Endpoint,
+}
+
+struct User {
+    name: String,
+    rights: u32,
+    /// Identity session of the login
+    session: u64,
+}
+
+pub struct Session<T: Transport> {
+    transport: T,
+    identity: Identity,
+    shell: String,
+    state: State,
+    started: u64,
+    user: Option<User>,
+    channels: Vec<Channel>,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+    pub stats: SessionStats,
+}
+
+impl<T: Transport> Session<T> {
+    /// A connection accepted at `now`, whose shell channels run `shell`
+    /// unless they name a command
+    pub fn new(transport: T, identity: Identity, shell: &str, now: u64) -> Self {
+        let mut session = Self {
+            transport,
+            identity,
+            shell: String::from(shell),
+            state: State::Auth,
+            started: now,
+            user: None,
+            channels: Vec::new(),
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+            stats: SessionStats::default(),
+        };
+        wire::encode_hello(&identity.host_key, &identity.challenge, &identity.signature, &mut session.outbound);
+        session
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Programs running for the channels of the session
+    pub fn pids(&self) -> Vec<u64> {
+        self.channels
+            .iter()
+            .filter_map(|channel| match channel.endpoint {
+                Endpoint::Process { pid, .. } => Some(pid),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Close the connection, killing its programs and closing its files
+    pub fn close(&mut self, host: &mut dyn ShellHost) {
+        for channel in self.channels.drain(..) {
+            release(host, &channel.endpoint);
+        }
+        if let Some(user) = self.user.take() {
+            host.close_session(user.session);
+        }
+        self.state = State::Closed;
+        self.transport.close();
+    }
+
+    /// Read, handle and answer what the client sent, and move downloads
+    /// along; the outcome of a login attempt handled on the way
+    pub fn poll(&mut self, keys: &AuthorizedKeys, host: &mut dyn ShellHost, now: u64) -> Option<Login> {
+        if self.state == State::Closed {
+            return None;
+        }
+        if self.state == State::Auth && now.saturating_sub(self.started) > AUTH_TIMEOUT_NS {
+            self.close(host);
+            return None;
+        }
+        self.receive(host);
+        let mut login = None;
+        while self.state == State::Auth || self.state == State::Open {
+            if wire::oversized(&self.inbound) {
+                self.close(host);
+                return login;
+            }
+            let Some(length) = wire::frame_length(&self.inbound) else {
+                break;
+            };
+            let frame = ClientFrame::decode(&self.inbound[..length]);
+            if !self.handle(frame, keys, host, &mut login) {
+                break;
+            }
+            self.inbound.drain(..length);
+        }
+        if self.state == State::Open {
+            self.send_downloads(host);
+        }
+        self.flush(host);
+        login
+    }
+
+    fn receive(&mut self, host: &mut dyn ShellHost) {
+        let mut buffer = [0u8; READ_SIZE];
+        // A whole frame of input is enough; the rest stays in the socket
+        while self.state != State::Closed && self.inbound.len() < wire::FRAME_HEADER_SIZE + MAX_PAYLOAD {
+            match self.transport.read(&mut buffer) {
+                Ok(0) | Err(IoError::Closed) => {
+                    self.close(host);
+                    return;
+                }
+                Ok(read) => {
+                    self.inbound.extend_from_slice(&buffer[..read]);
+                    self.stats.bytes_received += read as u64;
+                }
+                Err(IoError::WouldBlock) => return,
+            }
+        }
+    }
+
+    fn flush(&mut self, host: &mut dyn ShellHost) {
+        while !self.outbound.is_empty() && self.state != State::Closed {
+            match self.transport.write(&self.outbound) {
+                Ok(written) => {
+                    self.outbound.drain(..written);
+                    self.stats.bytes_sent += written as u64;
+                }
+                Err(IoError::WouldBlock) => return,
+                Err(IoError::Closed) => {
+                    self.close(host);
+                    return;
+                }
+            }
+        }
+        if self.state == State::Closing {
+            self.close(host);
+        }
+    }
+
+    /// Handle a whole frame; false to leave it queued until it can be taken
+    fn handle(
+        &mut self,
+        frame: Option<ClientFrame>,
+        keys: &AuthorizedKeys,
+        host: &mut dyn ShellHost,
+        login: &mut Option<Login>,
+    ) -> bool {
+        let Some(frame) = frame else {
+            self.state = State::Closing;
+            return true;
+        };
+        match (self.state, frame) {
+            (State::Auth, ClientFrame::Auth { public_key, signature }) => {
+                let identity = &self.identity;
+                let accepted = keys
+                    .authenticate(&identity.challenge, &identity.host_key, &identity.binding, &public_key, &signature)
+                    .and_then(|key| host.open_session(&key.name).ok().map(|session| (key, session)));
+                match accepted {
+                    Some((key, session)) => {
+                        let mut payload = key.rights.to_le_bytes().to_vec();
+                        payload.extend_from_slice(&(key.name.len() as u32).to_le_bytes());
+                        payload.extend_from_slice(key.name.as_bytes());
+                        wire::encode(wire::FRAME_AUTH_OK, 0, &payload, &mut self.outbound);
+                        self.user = Some(User { name: key.name.clone(), rights: key.rights, session });
+                        *login = Some(Login::Accepted { key: key.id, name: key.name.clone() });
+                        self.state = State::Open;
+                    }
+                    None => {
+                        wire::encode(wire::FRAME_AUTH_FAILED, 0, &[], &mut self.outbound);
+                        *login = Some(Login::Rejected);
+                        self.state = State::Closing;
+                    }
+                }
+            }
+            (State::Open, ClientFrame::Open { channel, kind }) => self.open(channel, kind, host),
+            (State::Open, ClientFrame::Data { channel, data }) => return self.data(channel, &data, host),
+            (State::Open, ClientFrame::Window { channel, size }) => {
+                if let Some(Endpoint::Process { pty: Some(pty), .. }) = self.endpoint(channel) {
+                    pty.size = size;
+                }
+            }
+            (State::Open, ClientFrame::Eof { channel }) => self.eof(channel, host),
+            (State::Open, ClientFrame::Close { channel }) => {
+                if let Some(index) = self.channels.iter().position(|entry| entry.id == channel) {
+                    let channel = self.channels.remove(index);
+                    release(host, &channel.endpoint);
+                }
+            }
+            // Anything else is a protocol error
+            _ => self.state = State::Closing,
+        }
+        true
+    }
+
+    fn endpoint(&mut self, channel: u32) -> Option<&mut Endpoint> {
+        self.channels.iter_mut().find(|entry| entry.id == channel).map(|entry| &mut entry.endpoint)
+    }
+
+    fn open(&mut self, channel: u32, kind: ChannelKind, host: &mut dyn ShellHost) {
+        let rights = self.user.as_ref().map_or(0, |user| user.rights);
+        let required = match kind {
+            ChannelKind::Shell { .. } => RIGHT_SHELL,
+            ChannelKind::Exec { .. } => RIGHT_EXEC,
+            ChannelKind::Upload { .. } => RIGHT_UPLOAD,
+            ChannelKind::Download { .. } => RIGHT_DOWNLOAD,
+        };
+        let opened = if rights & required == 0 {
+            Err(STATUS_EPERM)
+        } else if self.channels.iter().any(|entry| entry.id == channel) {
+            Err(STATUS_EEXIST)
+        } else if self.channels.len() >= MAX_CHANNELS {
+            Err(STATUS_ENOSPC)
+        } else {
+            self.start(kind, host)
+        };
+        match opened {
+            Ok((command, endpoint)) => {
+                self.channels.push(Channel { id: channel, command, endpoint });
+                wire::encode(wire::FRAME_OPEN_OK, channel, &[], &mut self.outbound);
+            }
+            Err(status) => wire::encode(wire::FRAME_OPEN_FAILED, channel, &status.to_le_bytes(), &mut self.outbound),
+        }
+    }
+
+    fn start(&self, kind: ChannelKind, host: &mut dyn ShellHost) -> Result<(String, Endpoint), i32> {
+        let (command, pty) = match kind {
+            ChannelKind::Upload { path } => {
+                let file = host.open_file(&path, true)?;
+                return Ok((path, Endpoint::Upload { file }));
+            }
+            ChannelKind::Download { path } => {
+                let file = host.open_file(&path, false)?;
+                return Ok((path, Endpoint::Download { file }));
+            }
+            ChannelKind::Shell { size, command } if command.is_empty() => {
+                (self.shell.clone(), Some(LineDiscipline::new(size)))
+            }
+            ChannelKind::Shell { size, command } => (command, Some(LineDiscipline::new(size))),
+            ChannelKind::Exec { command } => (command, None),
+        };
+        if command.trim().is_empty() {
+            return Err(STATUS_EINVAL);
+        }
+        let session = self.user.as_ref().map_or(0, |user| user.session);
+        let pid = host.spawn(&command, session)?;
+        Ok((command, Endpoint::Process { pid, pty, input: Vec::new(), input_closed: false }))
+    }
+
+    fn data(&mut self, channel: u32, data: &[u8], host: &mut dyn ShellHost) -> bool {
+        let mut echo = Vec::new();
+        let mut failed = None;
+        match self.endpoint(channel) {
+            Some(Endpoint::Process { pid, pty: Some(pty), .. }) => {
+                if pty.input(data, &mut echo) == Some(Signal::Interrupt) {
+                    host.kill(*pid);
+                }
+            }
+            Some(Endpoint::Process { input, .. }) => {
+                // Held until the program has read what came before
+                if !input.is_empty() && input.len() + data.len() > MAX_PENDING_INPUT {
+                    return false;
+                }
+                input.extend_from_slice(data);
+            }
+            Some(Endpoint::Upload { file }) => {
+                let mut written = 0;
+                while written < data.len() {
+                    match host.write_file(*file, &data[written..]) {
+                        Ok(0) => {
+                            failed = Some(STATUS_ENOSPC);
+                            break;
+                        }
+                        Ok(count) => written += count,
+                        Err(status) => {
+                            failed = Some(status);
+                            break;
+                        }
+                    }
+                }
+            }
+            // Data for a download or a channel closed meanwhile is dropped
+            Some(Endpoint::Download { .. }) | None => {}
+        }
+        if !echo.is_empty() {
+            wire::encode_data(channel, &echo, &mut self.outbound);
+        }
+        if let Some(status) = failed {
+            self.finish(channel, status, host);
+        }
+        true
+    }
+
+    fn eof(&mut self, channel: u32, host: &mut dyn ShellHost) {
+        match self.endpoint(channel) {
+            Some(Endpoint::Process { input_closed, .. }) => *input_closed = true,
+            Some(Endpoint::Upload { .. }) => self.finish(channel, STATUS_OK, host),
+            _ => {}
+        }
+    }
+
+    /// End a channel: EOF and EXIT with `status`, then CLOSE
+    fn finish(&mut self, channel: u32, status: i32, host: &mut dyn ShellHost) {
+        if let Some(index) = self.channels.iter().position(|entry| entry.id == channel) {
+            let removed = self.channels.remove(index);
+            release(host, &removed.endpoint);
+        }
+        wire::encode(wire::FRAME_EOF, channel, &[], &mut self.outbound);
+        wire::encode(wire::FRAME_EXIT, channel, &status.to_le_bytes(), &mut self.outbound);
+        wire::encode(wire::FRAME_CLOSE, channel, &[], &mut self.outbound);
+    }
+
+    /// Read the files of downloads into DATA frames while output is low
+    fn send_downloads(&mut self, host: &mut dyn ShellHost) {
+        let mut buffer = vec![0u8; MAX_PAYLOAD];
+        let mut index = 0;
+        while index < self.channels.len() {
+            let (channel, file) = match self.channels[index].endpoint {
+                Endpoint::Download { file } => (self.channels[index].id, file),
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            if self.outbound.len() >= OUTPUT_HIGH_WATER {
+                return;
+            }
+            match host.read_file(file, &mut buffer) {
+                Ok(0) => self.finish(channel, STATUS_OK, host),
+                Ok(read) => {
+                    wire::encode_data(channel, &buffer[..read], &mut self.outbound);
+                    index += 1;
+                }
+                Err(status) => self.finish(channel, status, host),
+            }
+        }
+    }
+
+    fn process(&mut self, pid: u64) -> Option<&mut Channel> {
+        self.channels
+            .iter_mut()
+            .find(|entry| matches!(entry.endpoint, Endpoint::Process { pid: owner, .. } if owner == pid))
+    }
+
+    /// Channel details for the program `pid`, None if it is not one of ours
+    pub fn attach(&mut self, pid: u64) -> Option<Attachment> {
+        let (user, rights) = self.user.as_ref().map(|user| (user.name.clone(), user.rights))?;
+        let channel = self.process(pid)?;
+        let (mode, size) = match &channel.endpoint {
+            Endpoint::Process { pty: Some(pty), .. } => (Some(pty.mode), pty.size),
+            _ => (None, WindowSize { columns: 0, rows: 0 }),
+        };
+        Some(Attachment { mode, size, command: channel.command.clone(), user, rights })
+    }
+
+    /// Input for the program `pid`: EAGAIN while there is none, empty at
+    /// the end of file
+    pub fn terminal_read(&mut self, pid: u64, max: usize) -> Result<Vec<u8>, i32> {
+        let Some(Channel { endpoint: Endpoint::Process { pty, input, input_closed, .. }, .. }) = self.process(pid)
+        else {
+            return Err(STATUS_EINVAL);
+        };
+        match pty {
+            Some(pty) if pty.readable() => return Ok(pty.read(max)),
+            None if !input.is_empty() => {
+                let count = max.min(input.len());
+                return Ok(input.drain(..count).collect());
+            }
+            _ => {}
+        }
+        if *input_closed {
+            Ok(Vec::new())
+        } else {
+            Err(STATUS_EAGAIN)
+        }
+    }
+
+    /// Output of the program `pid`; bytes taken, EAGAIN while the client
+    /// is behind
+    pub fn terminal_write(&mut self, pid: u64, data: &[u8], host: &mut dyn ShellHost) -> Result<usize, i32> {
+        if self.outbound.len() >= OUTPUT_HIGH_WATER {
+            return Err(STATUS_EAGAIN);
+        }
+        let count = data.len().min(MAX_PAYLOAD);
+        let Some(channel) = self.process(pid) else {
+            return Err(STATUS_EINVAL);
+        };
+        let id = channel.id;
+        let mut translated = Vec::new();
+        let output = match &channel.endpoint {
+            Endpoint::Process { pty: Some(pty), .. } => {
+                pty.output(&data[..count], &mut translated);
+                &translated[..]
+            }
+            _ => &data[..count],
+        };
+        wire::encode_data(id, output, &mut self.outbound);
+        self.flush(host);
+        Ok(count)
+    }
+
+    /// Set the line discipline mode of the program `pid`
+    pub fn set_mode(&mut self, pid: u64, mode: u32) -> Result<(), i32> {
+        match self.process(pid) {
+            Some(Channel { endpoint: Endpoint::Process { pty: Some(pty), .. }, .. }) => {
+                pty.set_mode(mode);
+                Ok(())
+            }
+            _ => Err(STATUS_EINVAL),
+        }
+    }
+
+    /// The program `pid` exited with `code`
+    pub fn exited(&mut self, pid: u64, code: i32, host: &mut dyn ShellHost) {
+        if let Some(id) = self.process(pid).map(|channel| channel.id) {
+            // Already gone: nothing left to kill
+            if let Some(index) = self.channels.iter().position(|entry| entry.id == id) {
+                self.channels.remove(index);
+            }
+            wire::encode(wire::FRAME_EOF, id, &[], &mut self.outbound);
+            wire::encode(wire::FRAME_EXIT, id, &code.to_le_bytes(), &mut self.outbound);
+            wire::encode(wire::FRAME_CLOSE, id, &[], &mut self.outbound);
+            self.flush(host);
+        }
+    }
+}
+
+/// Kill the program or close the file behind a channel
+fn release(host: &mut dyn ShellHost, endpoint: &Endpoint) {
+    match *endpoint {
+        Endpoint::Process { pid, .. } => host.kill(pid),
+        Endpoint::Upload { file } | Endpoint::Download { file } => host.close_file(file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::client_message;
+    use orion_crypto::ed25519;
+
+    #[derive(Default)]
+    struct MockTransport {
+        input: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    impl Transport for MockTransport {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+            if self.input.is_empty() {
+                return Err(IoError::WouldBlock);
+            }
+            let length = buffer.len().min(self.input.len());
+            buffer[..length].copy_from_slice(&self.input[..length]);
+            self.input.drain(..length);
+            Ok(length)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+            self.output.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn close(&mut self) {}
+    }
+
+    #[derive(Default)]
+    struct MockHost {
+        /// Accounts with an identity session open
+        sessions: Vec<String>,
+        spawned: Vec<(String, u64)>,
+        killed: Vec<u64>,
+    }
+
+    impl ShellHost for MockHost {
+        fn open_session(&mut self, user: &str) -> Result<u64, i32> {
+            if user != "ops" {
+                return Err(STATUS_EPERM);
+            }
+            self.sessions.push(String::from(user));
+            Ok(0x5e55)
+        }
+
+        fn close_session(&mut self, session: u64) {
+            assert_eq!(session, 0x5e55);
+            self.sessions.pop();
+        }
+
+        fn spawn(&mut self, command: &str, session: u64) -> Result<u64, i32> {
+            self.spawned.push((String::from(command), session));
+            Ok(100 + self.spawned.len() as u64)
+        }
+
+        fn kill(&mut self, pid: u64) {
+            self.killed.push(pid);
+        }
+
+        fn open_file(&mut self, _path: &str, _write: bool) -> Result<u64, i32> {
+            Err(STATUS_EPERM)
+        }
+
+        fn read_file(&mut self, _file: u64, _buffer: &mut [u8]) -> Result<usize, i32> {
+            Ok(0)
+        }
+
+        fn write_file(&mut self, _file: u64, data: &[u8]) -> Result<usize, i32> {
+            Ok(data.len())
+        }
+
+        fn close_file(&mut self, _file: u64) {}
+    }
+
+    fn frame(frame_type: u8, channel: u32, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        wire::encode(frame_type, channel, payload, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_logs_in_and_runs_a_shell() {
+        let seed = [0x42u8; 32];
+        let mut keys = AuthorizedKeys::new();
+        let id = keys.add("ops", RIGHT_SHELL, ed25519::public_key(&seed)).unwrap();
+        let identity = Identity {
+            host_key: [7; PUBLIC_KEY_SIZE],
+            challenge: [3; CHALLENGE_SIZE],
+            binding: [4; BINDING_SIZE],
+            signature: [0; 64],
+        };
+        let mut host = MockHost::default();
+        let mut session = Session::new(MockTransport::default(), identity, "/bin/osh", 0);
+
+        let mut auth = ed25519::public_key(&seed).to_vec();
+        let message = client_message(&identity.challenge, &identity.host_key, &identity.binding);
+        auth.extend_from_slice(&ed25519::sign(&seed, &message));
+        session.transport.input = frame(wire::FRAME_AUTH, 0, &auth);
+        assert_eq!(session.poll(&keys, &mut host, 1), Some(Login::Accepted { key: id, name: String::from("ops") }));
+
+        // A shell running the login shell, and an exec the key may not run
+        let mut open = vec![wire::KIND_SHELL, 80, 0, 24, 0];
+        open.extend_from_slice(&0u32.to_le_bytes());
+        session.transport.input = frame(wire::FRAME_OPEN, 1, &open);
+        let mut exec = vec![wire::KIND_EXEC];
+        exec.extend_from_slice(&2u32.to_le_bytes());
+        exec.extend_from_slice(b"ls");
+        session.transport.input.extend(frame(wire::FRAME_OPEN, 2, &exec));
+        session.transport.input.extend(frame(wire::FRAME_DATA, 1, b"ls\r"));
+        session.transport.output.clear();
+        assert_eq!(session.poll(&keys, &mut host, 2), None);
+        assert_eq!(host.spawned, [(String::from("/bin/osh"), 0x5e55)]);
+        let mut expected = frame(wire::FRAME_OPEN_OK, 1, &[]);
+        expected.extend(frame(wire::FRAME_OPEN_FAILED, 2, &STATUS_EPERM.to_le_bytes()));
+        expected.extend(frame(wire::FRAME_DATA, 1, b"ls\r\n"));
+        assert_eq!(core::mem::take(&mut session.transport.output), expected);
+
+        // Only the program of the channel reaches it
+        assert_eq!(session.pids(), [101]);
+        assert_eq!(session.attach(101).map(|attachment| attachment.mode), Some(Some(crate::pty::MODE_DEFAULT)));
+        assert_eq!(session.terminal_read(102, 64), Err(STATUS_EINVAL));
+        assert_eq!(session.terminal_read(101, 64), Ok(b"ls\n".to_vec()));
+        assert_eq!(session.terminal_read(101, 64), Err(STATUS_EAGAIN));
+        assert_eq!(session.terminal_write(101, b"a\n", &mut host), Ok(2));
+        assert_eq!(core::mem::take(&mut session.transport.output), frame(wire::FRAME_DATA, 1, b"a\r\n"));
+
+        session.exited(101, 0, &mut host);
+        let mut expected = frame(wire::FRAME_EOF, 1, &[]);
+        expected.extend(frame(wire::FRAME_EXIT, 1, &0i32.to_le_bytes()));
+        expected.extend(frame(wire::FRAME_CLOSE, 1, &[]));
+        assert_eq!(session.transport.output, expected);
+        assert!(session.pids().is_empty() && host.killed.is_empty());
+
+        // Closing the connection logs the session out
+        assert_eq!(host.sessions, ["ops"]);
+        session.close(&mut host);
+        assert!(host.sessions.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_a_key_without_an_account() {
+        let seed = [0x24u8; 32];
+        let mut keys = AuthorizedKeys::new();
+        keys.add("backup", RIGHT_SHELL, ed25519::public_key(&seed)).unwrap();
+        let identity = Identity {
+            host_key: [7; PUBLIC_KEY_SIZE],
+            challenge: [3; CHALLENGE_SIZE],
+            binding: [4; BINDING_SIZE],
+            signature: [0; 64],
+        };
+        let mut host = MockHost::default();
+        let mut session = Session::new(MockTransport::default(), identity, "/bin/osh", 0);
+
+        let mut auth = ed25519::public_key(&seed).to_vec();
+        let message = client_message(&identity.challenge, &identity.host_key, &identity.binding);
+        auth.extend_from_slice(&ed25519::sign(&seed, &message));
+        session.transport.input = frame(wire::FRAME_AUTH, 0, &auth);
+        assert_eq!(session.poll(&keys, &mut host, 1), Some(Login::Rejected));
+        assert!(session.transport.output.ends_with(&frame(wire::FRAME_AUTH_FAILED, 0, &[])));
+        assert!(session.is_closed() && host.sessions.is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+/*
+ * Orion Operating System - Remote Shell Wire Protocol
+ *
+ * Frames exchanged with a client over the TLS connection. All fields are
+ * little-endian; every frame is
+ *
+ *   type:u8 channel:u32 length:u32 payload
+ *
+ * and frames of the handshake use channel 0. The server speaks first:
+ *
+ *   HELLO        version:u32 host_key[32] challenge[32] signature[64]
+ *   AUTH         public_key[32] signature[64]
+ *   AUTH_OK      rights:u32 name
+ *   AUTH_FAILED  (empty), then the server closes
+ *
+ * HELLO carries the Ed25519 host key and the challenge of the connection
+ * signed with it together with the TLS binding of the connection (see
+ * keys.rs), so a client that pinned the host key knows it reached that
+ * host over this very TLS session, whatever the certificate says. AUTH
+ * signs the challenge, the host key and the binding with the client's
+ * own key. The binding is never sent: each end exports it from its side
+ * of the TLS session. The server speaks once the TLS handshake is over.
+ *
+ * Once logged in, the client opens channels with ids of its choosing
+ * (not 0) and the server answers each OPEN with OPEN_OK or OPEN_FAILED:
+ *
+ *   OPEN         kind:u8 ...            SHELL   columns:u16 rows:u16 command
+ *                                       EXEC    command
+ *                                       UPLOAD  path
+ *                                       DOWNLOAD path
+ *   OPEN_OK      (empty)
+ *   OPEN_FAILED  status:i32
+ *   DATA         bytes
+ *   WINDOW       columns:u16 rows:u16
+ *   EOF          (empty)
+ *   EXIT         status:i32
+ *   CLOSE        (empty)
+ *
+ * DATA flows both ways: keys and standard input to a program, its output
+ * back; file contents to an upload and from a download. EOF ends what
+ * the sender has to say on a channel, EXIT reports how it ended (the
+ * program's exit code, or 0 or a negative errno for a transfer), and
+ * CLOSE from either side forgets the channel, killing a program still
+ * running. Strings are a `len: u32` followed by UTF-8 bytes; an empty
+ * SHELL command runs the login shell.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use orion_crypto::ed25519::{PUBLIC_KEY_SIZE, SIGNATURE_SIZE};
+
+use crate::keys::CHALLENGE_SIZE;
+use crate::pty::WindowSize;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Frame types
+pub const FRAME_HELLO: u8 = 1;
+pub const FRAME_AUTH: u8 = 2;
+pub const FRAME_AUTH_OK: u8 = 3;
+pub const FRAME_AUTH_FAILED: u8 = 4;
+pub const FRAME_OPEN: u8 = 10;
+pub const FRAME_OPEN_OK: u8 = 11;
+pub const FRAME_OPEN_FAILED: u8 = 12;
+pub const FRAME_DATA: u8 = 13;
+pub const FRAME_WINDOW: u8 = 14;
+pub const FRAME_EOF: u8 = 15;
+pub const FRAME_EXIT: u8 = 16;
+pub const FRAME_CLOSE: u8 = 17;
+
+// Channel kinds
+pub const KIND_SHELL: u8 = 1;
+pub const KIND_EXEC: u8 = 2;
+pub const KIND_UPLOAD: u8 = 3;
+pub const KIND_DOWNLOAD: u8 = 4;
+
+pub const FRAME_HEADER_SIZE: usize = 9;
+
+/// Largest payload of a frame; DATA is cut to it
+pub const MAX_PAYLOAD: usize = 32 * 1024;
+
+/// Longest command or path of an OPEN
+pub const MAX_COMMAND: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelKind {
+    Shell { size: WindowSize, command: String },
+    Exec { command: String },
+    Upload { path: String },
+    Download { path: String },
+}
+
+/// Frame sent by a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientFrame {
+    Auth { public_key: [u8; PUBLIC_KEY_SIZE], signature: [u8; SIGNATURE_SIZE] },
+    Open { channel: u32, kind: ChannelKind },
+    Data { channel: u32, data: Vec<u8> },
+    Window { channel: u32, size: WindowSize },
+    Eof { channel: u32 },
+    Close { channel: u32 },
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decode a `len, utf-8 bytes` field no longer than MAX_COMMAND
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let len = read_u32(data, offset)? as usize;
+    if len > MAX_COMMAND {
+        return None;
+    }
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some(String::from(core::str::from_utf8(bytes).ok()?))
+}
+
+fn read_window(data: &[u8], offset: usize) -> Option<WindowSize> {
+    Some(WindowSize { columns: read_u16(data, offset)?, rows: read_u16(data, offset + 2)? })
+}
+
+/// Length of the frame at the head of `data`, None while incomplete
+pub fn frame_length(data: &[u8]) -> Option<usize> {
+    let length = FRAME_HEADER_SIZE + read_u32(data, 5)? as usize;
+    (data.len() >= length).then_some(length)
+}
+
+/// Whether the frame at the head of `data` announces more than MAX_PAYLOAD
+pub fn oversized(data: &[u8]) -> bool {
+    read_u32(data, 5).is_some_and(|length| length as usize > MAX_PAYLOAD)
+}
+
+impl ClientFrame {
+    /// Decode one whole frame, as delimited by frame_length
+    pub fn decode(frame: &[u8]) -> Option<Self> {
+        let channel = read_u32(frame, 1)?;
+        let payload = frame.get(FRAME_HEADER_SIZE..)?;
+        match frame[0] {
+            FRAME_AUTH => Some(ClientFrame::Auth {
+                public_key: payload.get(..PUBLIC_KEY_SIZE)?.try_into().ok()?,
+                signature: payload.get(PUBLIC_KEY_SIZE..PUBLIC_KEY_SIZE + SIGNATURE_SIZE)?.try_into().ok()?,
+            }),
+            FRAME_OPEN if channel != 0 => {
+                let kind = match *payload.first()? {
+                    KIND_SHELL => {
+                        ChannelKind::Shell { size: read_window(payload, 1)?, command: read_string(payload, 5)? }
+                    }
+                    KIND_EXEC => ChannelKind::Exec { command: read_string(payload, 1)? },
+                    KIND_UPLOAD => ChannelKind::Upload { path: read_string(payload, 1)? },
+                    KIND_DOWNLOAD => ChannelKind::Download { path: read_string(payload, 1)? },
+                    _ => return None,
+                };
+                Some(ClientFrame::Open { channel, kind })
+            }
+            FRAME_DATA => Some(ClientFrame::Data { channel, data: payload.to_vec() }),
+            FRAME_WINDOW => Some(ClientFrame::Window { channel, size: read_window(payload, 0)? }),
+            FRAME_EOF => Some(ClientFrame::Eof { channel }),
+            FRAME_CLOSE => Some(ClientFrame::Close { channel }),
+            _ => None,
+        }
+    }
+}
+
+/// Append a frame to `out`
+pub fn encode(frame_type: u8, channel: u32, payload: &[u8], out: &mut Vec<u8>) {
+    out.push(frame_type);
+    out.extend_from_slice(&channel.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Append DATA frames carrying `data`, cut to MAX_PAYLOAD
+pub fn encode_data(channel: u32, data: &[u8], out: &mut Vec<u8>) {
+    for chunk in data.chunks(MAX_PAYLOAD) {
+        encode(FRAME_DATA, channel, chunk, out);
+    }
+}
+
+pub fn encode_hello(
+    host_key: &[u8; PUBLIC_KEY_SIZE],
+    challenge: &[u8; CHALLENGE_SIZE],
+    signature: &[u8; SIGNATURE_SIZE],
+    out: &mut Vec<u8>,
+) {
+    let mut payload = Vec::with_capacity(4 + PUBLIC_KEY_SIZE + CHALLENGE_SIZE + SIGNATURE_SIZE);
+    payload.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    payload.extend_from_slice(host_key);
+    payload.extend_from_slice(challenge);
+    payload.extend_from_slice(signature);
+    encode(FRAME_HELLO, 0, &payload, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_decodes_client_frames() {
+        let mut payload = vec![KIND_SHELL];
+        payload.extend_from_slice(&120u16.to_le_bytes());
+        payload.extend_from_slice(&40u16.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        let mut data = Vec::new();
+        encode(FRAME_OPEN, 3, &payload, &mut data);
+        encode(FRAME_EOF, 3, &[], &mut data);
+
+        let length = frame_length(&data).unwrap();
+        assert_eq!(length, FRAME_HEADER_SIZE + payload.len());
+        let size = WindowSize { columns: 120, rows: 40 };
+        let open = ClientFrame::Open { channel: 3, kind: ChannelKind::Shell { size, command: String::new() } };
+        assert_eq!(ClientFrame::decode(&data[..length]), Some(open));
+        assert_eq!(ClientFrame::decode(&data[length..]), Some(ClientFrame::Eof { channel: 3 }));
+
+        // Incomplete, oversized, and on the handshake channel
+        assert_eq!(frame_length(&data[..length - 1]), None);
+        let mut huge = vec![FRAME_DATA, 1, 0, 0, 0];
+        huge.extend_from_slice(&(MAX_PAYLOAD as u32 + 1).to_le_bytes());
+        assert!(oversized(&huge) && !oversized(&data));
+        data[1] = 0;
+        assert_eq!(ClientFrame::decode(&data[..length]), None);
+
+        let mut out = Vec::new();
+        encode_data(5, &vec![0u8; MAX_PAYLOAD + 1], &mut out);
+        assert_eq!(out.len(), 2 * FRAME_HEADER_SIZE + MAX_PAYLOAD + 1);
+    }
+}
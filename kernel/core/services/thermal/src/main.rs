@@ -0,0 +1,354 @@
+/*
+ * Orion Operating System - Thermal Server
+ *
+ * Gathers the temperatures of the machine into thermal zones (see
+ * zone.rs) and cools it down when they trip. The CPU zone is read by the
+ * server itself from the cpufreq temperature attribute; drivers owning
+ * other sensors (GPU, NVMe drives, the battery) register their zones and
+ * report readings. Two cooling devices are built in:
+ *
+ *   cpufreq   caps the frequency of every CPU through the cpufreq
+ *             thermal ceiling, one eighth of the range per state
+ *   throttle  tells subscribers to slow their workloads down
+ *
+ * and drivers controlling fans register their own, which the server
+ * drives by sending them SET_STATE. Hot and critical trips are audited;
+ * the management server raises alerts from LIST_ZONES and the metrics
+ * exporter publishes the zones.
+ *
+ * Providers need the write right on the thermal capability, adding trips
+ * the admin right, listing and subscribing the read right.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ, CAP_WRITE};
+use orion_sys::{audit_emit, clock_get, cpufreq_show, cpufreq_store};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod protocol;
+mod zone;
+
+use protocol::*;
+use zone::{Owner, Thermal, ThermalError, Trip, TripEvent, TripKind, DEFAULT_HYSTERESIS_MC};
+
+/// Pause between two iterations of the run loop
+const POLL_INTERVAL_NS: u64 = 100_000_000;
+/// Pause between two cooling steps, and two readings of the CPU sensors
+const STEP_INTERVAL_NS: u64 = 1_000_000_000;
+
+const MAX_SUBSCRIBERS: usize = 64;
+const MAX_CPUS: u32 = 64;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+/// Provider id of the zones the server reads itself
+const SELF_PROVIDER: u64 = 0;
+
+// Built-in cooling devices
+const CPUFREQ_STEPS: u32 = 8;
+const THROTTLE_STEPS: u32 = 4;
+
+// Trips of the CPU zone, as x86 parts throttle themselves around 100 C
+const CPU_PASSIVE_MC: i32 = 85_000;
+const CPU_CRITICAL_MC: i32 = 100_000;
+
+const AUDIT_THERMAL_TRIP: u32 = 0x1201;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// Integer value of a cpufreq attribute of one CPU
+fn cpufreq_value(cpu: u32, name: &str) -> Option<i64> {
+    let mut buffer = [0u8; 32];
+    let length = cpufreq_show(cpu, name, &mut buffer).ok()?;
+    core::str::from_utf8(buffer.get(..length)?).ok()?.trim().parse().ok()
+}
+
+/// Hardware frequency range of a CPU, kHz
+struct CpuRange {
+    cpu: u32,
+    min_khz: u32,
+    max_khz: u32,
+}
+
+struct ThermalServer {
+    thermal: Thermal,
+    cpus: Vec<CpuRange>,
+    cpu_zone: Option<u32>,
+    /// Mask of the built-in cooling devices, bound to passive trips
+    builtin_cooling: u32,
+    next_step_ns: u64,
+    subscribers: BTreeSet<u64>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl ThermalServer {
+    fn new() -> Self {
+        let mut server = Self {
+            thermal: Thermal::new(),
+            cpus: Vec::new(),
+            cpu_zone: None,
+            builtin_cooling: 0,
+            next_step_ns: 0,
+            subscribers: BTreeSet::new(),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        };
+        server.probe_cpus();
+        server
+    }
+
+    fn probe_cpus(&mut self) {
+        for cpu in 0..MAX_CPUS {
+            match (cpufreq_value(cpu, "cpuinfo_min_freq"), cpufreq_value(cpu, "cpuinfo_max_freq")) {
+                (Some(min), Some(max)) => self.cpus.push(CpuRange { cpu, min_khz: min as u32, max_khz: max as u32 }),
+                _ => break,
+            }
+        }
+
+        if !self.cpus.is_empty() {
+            if let Ok(id) = self.thermal.register_cooling("cpufreq", CPUFREQ_STEPS, Owner::Builtin) {
+                self.builtin_cooling |= 1 << id;
+            }
+        }
+        if let Ok(id) = self.thermal.register_cooling("throttle", THROTTLE_STEPS, Owner::Builtin) {
+            self.builtin_cooling |= 1 << id;
+        }
+
+        // CPUs without a sensor driver leave the zone out
+        if self.cpus.iter().any(|range| cpufreq_value(range.cpu, "temperature").is_some()) {
+            self.cpu_zone = self.register_zone("cpu", SELF_PROVIDER, CPU_PASSIVE_MC, CPU_CRITICAL_MC).ok();
+        }
+    }
+
+    /// Register a zone with its default passive and critical trips
+    fn register_zone(
+        &mut self,
+        name: &str,
+        provider: u64,
+        passive_mc: i32,
+        critical_mc: i32,
+    ) -> Result<u32, ThermalError> {
+        let zone = self.thermal.register_zone(name, provider)?;
+        let trip = |kind, temp_mc, cooling| Trip { kind, temp_mc, hysteresis_mc: DEFAULT_HYSTERESIS_MC, cooling };
+        if passive_mc != 0 {
+            self.thermal.add_trip(zone, trip(TripKind::Passive, passive_mc, self.builtin_cooling))?;
+        }
+        if critical_mc != 0 {
+            self.thermal.add_trip(zone, trip(TripKind::Critical, critical_mc, 0))?;
+        }
+        Ok(zone)
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            let now = monotonic_ns();
+            if now >= self.next_step_ns {
+                self.next_step_ns = now + STEP_INTERVAL_NS;
+                self.step(now);
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn step(&mut self, now: u64) {
+        if let Some(zone) = self.cpu_zone {
+            // The package is as hot as its hottest core
+            let hottest = self.cpus.iter().filter_map(|range| cpufreq_value(range.cpu, "temperature")).max();
+            if let Some(temp_mc) = hottest {
+                if let Ok(events) = self.thermal.report(zone, SELF_PROVIDER, temp_mc as i32, now) {
+                    self.trip_events(&events);
+                }
+            }
+        }
+        let expired = self.thermal.expire(now);
+        self.trip_events(&expired);
+
+        for (device, state) in self.thermal.step_cooling() {
+            self.apply_cooling(device, state);
+        }
+    }
+
+    fn trip_events(&mut self, events: &[TripEvent]) {
+        for trip in events {
+            if trip.tripped && trip.kind >= TripKind::Hot {
+                let record = format!(
+                    "thermal-trip zone={} kind={} temp_mc={}",
+                    self.thermal.zones()[trip.zone as usize].name,
+                    trip.kind.as_u32(),
+                    trip.temp_mc
+                );
+                let _ = audit_emit(AUDIT_THERMAL_TRIP, record.as_bytes());
+            }
+            let message = event(
+                EVENT_TRIP,
+                &[trip.zone, trip.trip, trip.kind.as_u32(), trip.tripped as u32, trip.temp_mc as u32],
+            );
+            self.notify(&message);
+        }
+    }
+
+    fn apply_cooling(&mut self, device: u32, state: u32) {
+        let cooling = &self.thermal.cooling()[device as usize];
+        let max_state = cooling.max_state;
+        match cooling.owner {
+            Owner::Provider(endpoint) => {
+                self.ipc_channel.send(endpoint, &event(EVENT_SET_STATE, &[device, state]));
+            }
+            Owner::Builtin if cooling.name == "cpufreq" => {
+                for range in &self.cpus {
+                    // State 0 lifts the ceiling, the last state pins the minimum
+                    let ceiling = if state == 0 {
+                        0
+                    } else {
+                        let span = (range.max_khz - range.min_khz) as u64;
+                        range.max_khz - (span * state as u64 / max_state as u64) as u32
+                    };
+                    let _ = cpufreq_store(range.cpu, "thermal_max_freq", &format!("{}", ceiling));
+                }
+            }
+            Owner::Builtin => self.notify(&event(EVENT_THROTTLE, &[state, max_state])),
+        }
+        self.notify(&event(EVENT_COOLING, &[device, state]));
+    }
+
+    fn notify(&mut self, message: &[u8]) {
+        for &subscriber in &self.subscribers {
+            self.ipc_channel.send(subscriber, message);
+        }
+    }
+
+    fn list_zones(&self, payload: &mut Vec<u8>) {
+        for (id, zone) in self.thermal.zones().iter().enumerate() {
+            put_name(payload, &zone.name);
+            let worst = zone.worst().map(|kind| kind.as_u32() + 1).unwrap_or(0);
+            for value in
+                [id as u32, zone.temp_mc.is_some() as u32, zone.temp_mc.unwrap_or(0) as u32, worst, zone.tripped]
+            {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    fn list_cooling(&self, payload: &mut Vec<u8>) {
+        for (id, device) in self.thermal.cooling().iter().enumerate() {
+            put_name(payload, &device.name);
+            for value in [id as u32, device.state, device.max_state] {
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        let request = match ThermalRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            ThermalRequest::RegisterZone { .. }
+            | ThermalRequest::Report { .. }
+            | ThermalRequest::RegisterCooling { .. } => CAP_WRITE,
+            ThermalRequest::AddTrip { .. } => CAP_ADMIN,
+            _ => CAP_READ,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let sender = message.sender;
+        let mut payload = Vec::new();
+        let result = match request {
+            ThermalRequest::RegisterZone { name, passive_mc, critical_mc } => {
+                self.register_zone(&name, sender, passive_mc, critical_mc).map(|zone| {
+                    payload.extend_from_slice(&zone.to_le_bytes());
+                })
+            }
+            ThermalRequest::Report { zone, temp_mc } => {
+                self.thermal.report(zone, sender, temp_mc, monotonic_ns()).map(|events| self.trip_events(&events))
+            }
+            ThermalRequest::RegisterCooling { name, max_state } => {
+                self.thermal.register_cooling(&name, max_state, Owner::Provider(sender)).map(|device| {
+                    payload.extend_from_slice(&device.to_le_bytes());
+                })
+            }
+            ThermalRequest::AddTrip { zone, trip } => self.thermal.add_trip(zone, trip).map(|index| {
+                payload.extend_from_slice(&index.to_le_bytes());
+            }),
+            ThermalRequest::ListZones => {
+                self.list_zones(&mut payload);
+                Ok(())
+            }
+            ThermalRequest::ListCooling => {
+                self.list_cooling(&mut payload);
+                Ok(())
+            }
+            ThermalRequest::Subscribe => {
+                if self.subscribers.len() >= MAX_SUBSCRIBERS && !self.subscribers.contains(&sender) {
+                    Err(ThermalError::NoSpace)
+                } else {
+                    self.subscribers.insert(sender);
+                    Ok(())
+                }
+            }
+            ThermalRequest::Unsubscribe => {
+                self.subscribers.remove(&sender);
+                Ok(())
+            }
+        };
+
+        let status = match result {
+            Ok(()) => STATUS_OK,
+            Err(ThermalError::InvalidArgument) => STATUS_EINVAL,
+            Err(ThermalError::NotFound) => STATUS_ENOENT,
+            Err(ThermalError::NoSpace) => STATUS_ENOSPC,
+            Err(ThermalError::NotOwner) => STATUS_EPERM,
+        };
+        self.ipc_channel.send(sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    let mut server = ThermalServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
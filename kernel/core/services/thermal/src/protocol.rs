@@ -0,0 +1,171 @@
+/*
+ * Orion Operating System - Thermal Server Protocol
+ *
+ * IPC requests of the thermal server. All fields are little-endian;
+ * every message starts with a 32-bit opcode and every reply starts with a
+ * 32-bit signed status (0 or a negative errno). Temperatures are signed
+ * millidegrees Celsius and names are NUL-padded 16-byte fields.
+ *
+ * Providers (drivers owning a sensor or a fan):
+ *   REGISTER_ZONE    name passive:i32 critical:i32   -> zone:u32
+ *   REPORT           zone:u32 temp:i32               -> (empty)
+ *   REGISTER_COOLING name max_state:u32              -> device:u32
+ * Administration:
+ *   ADD_TRIP         zone:u32 kind:u32 temp:i32 hysteresis:i32 cooling:u32
+ *                                                    -> trip:u32
+ * Monitoring:
+ *   LIST_ZONES       (none)  -> { name id:u32 valid:u32 temp:i32 worst:u32
+ *                                 tripped:u32 }*
+ *   LIST_COOLING     (none)  -> { name id:u32 state:u32 max_state:u32 }*
+ *   SUBSCRIBE        (none)  -> (empty)
+ *   UNSUBSCRIBE      (none)  -> (empty)
+ *
+ * REGISTER_ZONE adds a passive trip bound to the built-in cooling devices
+ * and a critical trip; 0 leaves either out. Trip kinds are 0 passive,
+ * 1 active, 2 hot, 3 critical; `cooling` is a mask of device ids. `worst`
+ * is 1 + the kind of the most severe tripped trip, 0 when none is.
+ *
+ * Subscribers receive TRIP (zone:u32 trip:u32 kind:u32 tripped:u32
+ * temp:i32) when a trip is crossed, COOLING (device:u32 state:u32) when a
+ * device changes state and THROTTLE (state:u32 max_state:u32) when
+ * workloads should slow down. The provider owning a cooling device
+ * receives SET_STATE (device:u32 state:u32).
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::zone::{Trip, TripKind};
+
+// Opcodes
+pub const OP_REGISTER_ZONE: u32 = 1;
+pub const OP_REPORT: u32 = 2;
+pub const OP_REGISTER_COOLING: u32 = 3;
+pub const OP_ADD_TRIP: u32 = 4;
+pub const OP_LIST_ZONES: u32 = 5;
+pub const OP_LIST_COOLING: u32 = 6;
+pub const OP_SUBSCRIBE: u32 = 7;
+pub const OP_UNSUBSCRIBE: u32 = 8;
+
+// Events sent to subscribers
+pub const EVENT_TRIP: u32 = 0x8001;
+pub const EVENT_COOLING: u32 = 0x8002;
+pub const EVENT_THROTTLE: u32 = 0x8003;
+/// Sent to the provider owning a cooling device
+pub const EVENT_SET_STATE: u32 = 0x8004;
+
+pub const NAME_SIZE: usize = 16;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThermalRequest {
+    RegisterZone { name: String, passive_mc: i32, critical_mc: i32 },
+    Report { zone: u32, temp_mc: i32 },
+    RegisterCooling { name: String, max_state: u32 },
+    AddTrip { zone: u32, trip: Trip },
+    ListZones,
+    ListCooling,
+    Subscribe,
+    Unsubscribe,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_name(data: &[u8], offset: usize) -> Option<String> {
+    let field = data.get(offset..offset + NAME_SIZE)?;
+    let length = field.iter().position(|byte| *byte == 0).unwrap_or(NAME_SIZE);
+    Some(String::from(core::str::from_utf8(&field[..length]).ok()?))
+}
+
+/// Append `name` as a NUL-padded name field
+pub fn put_name(out: &mut Vec<u8>, name: &str) {
+    let length = name.len().min(NAME_SIZE);
+    out.extend_from_slice(&name.as_bytes()[..length]);
+    out.resize(out.len() + NAME_SIZE - length, 0);
+}
+
+impl ThermalRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_REGISTER_ZONE => Some(ThermalRequest::RegisterZone {
+                name: read_name(data, 4)?,
+                passive_mc: read_u32(data, 20)? as i32,
+                critical_mc: read_u32(data, 24)? as i32,
+            }),
+            OP_REPORT => Some(ThermalRequest::Report { zone: read_u32(data, 4)?, temp_mc: read_u32(data, 8)? as i32 }),
+            OP_REGISTER_COOLING => {
+                Some(ThermalRequest::RegisterCooling { name: read_name(data, 4)?, max_state: read_u32(data, 20)? })
+            }
+            OP_ADD_TRIP => Some(ThermalRequest::AddTrip {
+                zone: read_u32(data, 4)?,
+                trip: Trip {
+                    kind: TripKind::from_u32(read_u32(data, 8)?)?,
+                    temp_mc: read_u32(data, 12)? as i32,
+                    hysteresis_mc: read_u32(data, 16)? as i32,
+                    cooling: read_u32(data, 20)?,
+                },
+            }),
+            OP_LIST_ZONES => Some(ThermalRequest::ListZones),
+            OP_LIST_COOLING => Some(ThermalRequest::ListCooling),
+            OP_SUBSCRIBE => Some(ThermalRequest::Subscribe),
+            OP_UNSUBSCRIBE => Some(ThermalRequest::Unsubscribe),
+            _ => None,
+        }
+    }
+}
+
+/// Message made of `opcode` followed by `fields`
+pub fn event(opcode: u32, fields: &[u32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + 4 * fields.len());
+    out.extend_from_slice(&opcode.to_le_bytes());
+    for field in fields {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut message = OP_REGISTER_ZONE.to_le_bytes().to_vec();
+        put_name(&mut message, "nvme0");
+        message.extend_from_slice(&70_000i32.to_le_bytes());
+        message.extend_from_slice(&(-1i32).to_le_bytes());
+        assert_eq!(
+            ThermalRequest::decode(&message),
+            Some(ThermalRequest::RegisterZone { name: String::from("nvme0"), passive_mc: 70_000, critical_mc: -1 })
+        );
+        assert_eq!(ThermalRequest::decode(&message[..24]), None);
+
+        let message = event(OP_ADD_TRIP, &[1, 2, 95_000, 1000, 0b11]);
+        assert_eq!(
+            ThermalRequest::decode(&message),
+            Some(ThermalRequest::AddTrip {
+                zone: 1,
+                trip: Trip { kind: TripKind::Hot, temp_mc: 95_000, hysteresis_mc: 1000, cooling: 0b11 }
+            })
+        );
+        assert_eq!(ThermalRequest::decode(&event(OP_ADD_TRIP, &[1, 4, 0, 0, 0])), None);
+        assert_eq!(ThermalRequest::decode(&9u32.to_le_bytes()), None);
+    }
+}
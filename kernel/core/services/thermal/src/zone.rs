@@ -0,0 +1,366 @@
+/*
+ * Orion Operating System - Thermal Zones
+ *
+ * A zone is one temperature sensor reported by a provider: the CPU
+ * package, a GPU, an NVMe drive, the battery. Each zone carries trip
+ * points; a trip trips when the temperature reaches it and clears once
+ * it has fallen `hysteresis` below it, so a sensor hovering around the
+ * trip does not flap. Trips are bound to cooling devices by a mask of
+ * device ids.
+ *
+ * Cooling is step-wise: on every evaluation a device demanded by one of
+ * its tripped trips goes one state up, a device no longer demanded one
+ * state down, until it reaches its maximum or zero. A tripped critical
+ * trip sends every device to its maximum at once. A zone whose provider
+ * stopped reporting is treated as cold rather than leaving the machine
+ * throttled on a stale reading.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const MAX_ZONES: usize = 32;
+pub const MAX_TRIPS: usize = 8;
+/// Device ids are bits of a trip's cooling mask
+pub const MAX_COOLING: usize = 32;
+
+/// A zone without a report for this long is considered stale
+pub const STALE_NS: u64 = 30_000_000_000;
+
+/// Hysteresis of the trips given when a zone registers, millidegrees
+pub const DEFAULT_HYSTERESIS_MC: i32 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TripKind {
+    /// Slow the machine down: frequency caps, workload throttling
+    Passive,
+    /// Run a fan
+    Active,
+    /// Warn the administrator
+    Hot,
+    /// Every cooling device to its maximum and an alert
+    Critical,
+}
+
+impl TripKind {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(TripKind::Passive),
+            1 => Some(TripKind::Active),
+            2 => Some(TripKind::Hot),
+            3 => Some(TripKind::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            TripKind::Passive => 0,
+            TripKind::Active => 1,
+            TripKind::Hot => 2,
+            TripKind::Critical => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trip {
+    pub kind: TripKind,
+    pub temp_mc: i32,
+    pub hysteresis_mc: i32,
+    /// Cooling devices driven by the trip
+    pub cooling: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Zone {
+    pub name: String,
+    /// Endpoint allowed to report the zone's temperature
+    pub provider: u64,
+    pub temp_mc: Option<i32>,
+    pub updated_ns: u64,
+    pub trips: Vec<Trip>,
+    /// Tripped trips, one bit per index of `trips`
+    pub tripped: u32,
+}
+
+impl Zone {
+    /// Most severe tripped trip
+    pub fn worst(&self) -> Option<TripKind> {
+        self.trips
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.tripped & (1 << index) != 0)
+            .map(|(_, trip)| trip.kind)
+            .max()
+    }
+}
+
+/// Who carries out a cooling device's state changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    /// Implemented by the thermal server itself
+    Builtin,
+    /// A provider endpoint told about every change
+    Provider(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct CoolingDevice {
+    pub name: String,
+    pub owner: Owner,
+    pub max_state: u32,
+    pub state: u32,
+}
+
+/// Trip crossing noticed while evaluating a zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TripEvent {
+    pub zone: u32,
+    pub trip: u32,
+    pub kind: TripKind,
+    pub tripped: bool,
+    pub temp_mc: i32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThermalError {
+    InvalidArgument,
+    NotFound,
+    NoSpace,
+    /// The zone belongs to another provider
+    NotOwner,
+}
+
+#[derive(Default)]
+pub struct Thermal {
+    zones: Vec<Zone>,
+    cooling: Vec<CoolingDevice>,
+}
+
+impl Thermal {
+    pub fn new() -> Self {
+        Self { zones: Vec::new(), cooling: Vec::new() }
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    pub fn cooling(&self) -> &[CoolingDevice] {
+        &self.cooling
+    }
+
+    /// Register a zone, or return the id of the provider's zone of that
+    /// name when it registers again after a restart
+    pub fn register_zone(&mut self, name: &str, provider: u64) -> Result<u32, ThermalError> {
+        if name.is_empty() {
+            return Err(ThermalError::InvalidArgument);
+        }
+        if let Some(index) = self.zones.iter().position(|zone| zone.name == name) {
+            if self.zones[index].provider != provider {
+                return Err(ThermalError::NotOwner);
+            }
+            return Ok(index as u32);
+        }
+        if self.zones.len() >= MAX_ZONES {
+            return Err(ThermalError::NoSpace);
+        }
+        self.zones.push(Zone {
+            name: String::from(name),
+            provider,
+            temp_mc: None,
+            updated_ns: 0,
+            trips: Vec::new(),
+            tripped: 0,
+        });
+        Ok((self.zones.len() - 1) as u32)
+    }
+
+    /// Add a trip to a zone; a trip of the same kind and temperature is
+    /// replaced, so providers re-registering do not pile up duplicates
+    pub fn add_trip(&mut self, zone: u32, trip: Trip) -> Result<u32, ThermalError> {
+        if trip.hysteresis_mc < 0 || trip.cooling as u64 >= 1u64 << self.cooling.len() {
+            return Err(ThermalError::InvalidArgument);
+        }
+        let zone = self.zones.get_mut(zone as usize).ok_or(ThermalError::NotFound)?;
+        if let Some(index) = zone.trips.iter().position(|old| old.kind == trip.kind && old.temp_mc == trip.temp_mc) {
+            zone.trips[index] = trip;
+            return Ok(index as u32);
+        }
+        if zone.trips.len() >= MAX_TRIPS {
+            return Err(ThermalError::NoSpace);
+        }
+        zone.trips.push(trip);
+        Ok((zone.trips.len() - 1) as u32)
+    }
+
+    pub fn register_cooling(&mut self, name: &str, max_state: u32, owner: Owner) -> Result<u32, ThermalError> {
+        if name.is_empty() || max_state == 0 {
+            return Err(ThermalError::InvalidArgument);
+        }
+        if let Some(index) = self.cooling.iter().position(|device| device.name == name) {
+            if self.cooling[index].owner != owner {
+                return Err(ThermalError::NotOwner);
+            }
+            self.cooling[index].max_state = max_state;
+            return Ok(index as u32);
+        }
+        if self.cooling.len() >= MAX_COOLING {
+            return Err(ThermalError::NoSpace);
+        }
+        self.cooling.push(CoolingDevice { name: String::from(name), owner, max_state, state: 0 });
+        Ok((self.cooling.len() - 1) as u32)
+    }
+
+    /// Record a temperature and return the trips it crossed
+    pub fn report(&mut self, zone: u32, provider: u64, temp_mc: i32, now: u64) -> Result<Vec<TripEvent>, ThermalError> {
+        let entry = self.zones.get_mut(zone as usize).ok_or(ThermalError::NotFound)?;
+        if entry.provider != provider {
+            return Err(ThermalError::NotOwner);
+        }
+        entry.temp_mc = Some(temp_mc);
+        entry.updated_ns = now;
+        Ok(Self::evaluate(zone, entry))
+    }
+
+    /// Forget the readings of zones whose provider went quiet
+    pub fn expire(&mut self, now: u64) -> Vec<TripEvent> {
+        let mut events = Vec::new();
+        for (index, zone) in self.zones.iter_mut().enumerate() {
+            if zone.temp_mc.is_some() && now.saturating_sub(zone.updated_ns) >= STALE_NS {
+                zone.temp_mc = None;
+                events.extend(Self::evaluate(index as u32, zone));
+            }
+        }
+        events
+    }
+
+    fn evaluate(index: u32, zone: &mut Zone) -> Vec<TripEvent> {
+        let mut events = Vec::new();
+        for (trip_index, trip) in zone.trips.iter().enumerate() {
+            let bit = 1 << trip_index;
+            let was = zone.tripped & bit != 0;
+            let is = match zone.temp_mc {
+                Some(temp) if was => temp > trip.temp_mc - trip.hysteresis_mc,
+                Some(temp) => temp >= trip.temp_mc,
+                None => false,
+            };
+            if is != was {
+                zone.tripped ^= bit;
+                events.push(TripEvent {
+                    zone: index,
+                    trip: trip_index as u32,
+                    kind: trip.kind,
+                    tripped: is,
+                    temp_mc: zone.temp_mc.unwrap_or(0),
+                });
+            }
+        }
+        events
+    }
+
+    /// Take one cooling step; returns the devices whose state changed
+    pub fn step_cooling(&mut self) -> Vec<(u32, u32)> {
+        let mut demanded = 0u32;
+        let mut critical = false;
+        for zone in &self.zones {
+            for (index, trip) in zone.trips.iter().enumerate() {
+                if zone.tripped & (1 << index) != 0 {
+                    demanded |= trip.cooling;
+                    critical |= trip.kind == TripKind::Critical;
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (id, device) in self.cooling.iter_mut().enumerate() {
+            let state = if critical {
+                device.max_state
+            } else if demanded & (1 << id) != 0 {
+                (device.state + 1).min(device.max_state)
+            } else {
+                device.state.saturating_sub(1)
+            };
+            if state != device.state {
+                device.state = state;
+                changes.push((id as u32, state));
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROVIDER: u64 = 7;
+
+    fn passive(temp_mc: i32, cooling: u32) -> Trip {
+        Trip { kind: TripKind::Passive, temp_mc, hysteresis_mc: DEFAULT_HYSTERESIS_MC, cooling }
+    }
+
+    #[test]
+    fn test_trips_with_hysteresis() {
+        let mut thermal = Thermal::new();
+        let zone = thermal.register_zone("nvme0", PROVIDER).unwrap();
+        assert_eq!(thermal.register_zone("nvme0", PROVIDER), Ok(zone));
+        assert_eq!(thermal.register_zone("nvme0", 8), Err(ThermalError::NotOwner));
+        thermal.add_trip(zone, passive(70_000, 0)).unwrap();
+
+        assert!(thermal.report(zone, PROVIDER, 69_000, 1).unwrap().is_empty());
+        let events = thermal.report(zone, PROVIDER, 70_000, 2).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].tripped);
+        assert_eq!(thermal.zones()[0].worst(), Some(TripKind::Passive));
+        // Inside the hysteresis band the trip holds
+        assert!(thermal.report(zone, PROVIDER, 68_500, 3).unwrap().is_empty());
+        let events = thermal.report(zone, PROVIDER, 68_000, 4).unwrap();
+        assert!(!events[0].tripped);
+        assert_eq!(thermal.report(zone, 8, 90_000, 5), Err(ThermalError::NotOwner));
+
+        // A provider that went quiet does not keep the zone hot
+        thermal.report(zone, PROVIDER, 75_000, 10).unwrap();
+        assert!(thermal.expire(10 + STALE_NS - 1).is_empty());
+        let events = thermal.expire(10 + STALE_NS);
+        assert!(!events[0].tripped);
+        assert_eq!(thermal.zones()[0].temp_mc, None);
+    }
+
+    #[test]
+    fn test_cooling_steps_toward_demand() {
+        let mut thermal = Thermal::new();
+        let fan = thermal.register_cooling("fan0", 2, Owner::Provider(PROVIDER)).unwrap();
+        let cpu = thermal.register_cooling("cpufreq", 4, Owner::Builtin).unwrap();
+        assert_eq!(thermal.register_cooling("fan0", 2, Owner::Builtin), Err(ThermalError::NotOwner));
+        let zone = thermal.register_zone("cpu", PROVIDER).unwrap();
+        assert_eq!(thermal.add_trip(zone, passive(80_000, 1 << 2)), Err(ThermalError::InvalidArgument));
+        thermal.add_trip(zone, Trip { kind: TripKind::Active, ..passive(60_000, 1 << fan) }).unwrap();
+        thermal.add_trip(zone, passive(80_000, 1 << cpu)).unwrap();
+        thermal.add_trip(zone, Trip { kind: TripKind::Critical, ..passive(100_000, 0) }).unwrap();
+
+        thermal.report(zone, PROVIDER, 85_000, 1).unwrap();
+        assert_eq!(thermal.step_cooling(), [(fan, 1), (cpu, 1)]);
+        assert_eq!(thermal.step_cooling(), [(fan, 2), (cpu, 2)]);
+        assert_eq!(thermal.step_cooling(), [(cpu, 3)]);
+
+        // Critical maxes out everything at once
+        thermal.report(zone, PROVIDER, 100_000, 2).unwrap();
+        assert_eq!(thermal.zones()[0].worst(), Some(TripKind::Critical));
+        assert_eq!(thermal.step_cooling(), [(cpu, 4)]);
+
+        thermal.report(zone, PROVIDER, 65_000, 3).unwrap();
+        assert_eq!(thermal.step_cooling(), [(cpu, 3)]);
+        thermal.report(zone, PROVIDER, 40_000, 4).unwrap();
+        assert_eq!(thermal.step_cooling(), [(fan, 1), (cpu, 2)]);
+    }
+}
@@ -0,0 +1,414 @@
+/*
+ * Orion Operating System - Update Server
+ *
+ * Installs system updates on the disk orion-install laid out: an update
+ * is written to the system slot not running, read back, and made the
+ * slot the bootloader starts next, with a number of tries to complete a
+ * boot. Until one completes the previous slot stays bootable, and the
+ * bootloader goes back to it once the tries are used up (see
+ * lib/orion_install/src/update.rs and bootctl.rs).
+ *
+ * A boot completes when init confirms it, once the user interface is up.
+ * Confirmation is due within BOOT_CONFIRM_NS of the kernel start: a boot
+ * not confirmed by then has failed, and the server takes the slot out of
+ * use so that the next start returns to the other one whatever tries are
+ * left. While a boot is on trial no update is accepted, as it would
+ * overwrite the slot to fall back to.
+ *
+ * The server finds the system disk among the disks of the I/O server by
+ * its boot control partition. Reading the state needs CAP_READ, updating
+ * and confirming CAP_ADMIN. Commits and boot outcomes are audited.
+ *
+ * An image starts with its signed boot configuration (lib/orion_verity):
+ * the first WRITE has to carry it whole, signed by one of the trust
+ * anchors of the I/O server and describing an image of the size sent.
+ * Anything else is refused before a byte reaches the slot, so a slot only
+ * ever holds images the verity driver will serve.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use orion_blkio::{DiskClient, DiskInfo, Transport};
+use orion_cap::Capability;
+use orion_crypto::ed25519::PUBLIC_KEY_SIZE;
+use orion_install::bootctl::SLOT_SUCCESSFUL;
+use orion_install::{Disk, ImageWriter, InstallError, SystemDisk};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+use orion_sys::{audit_emit, clock_get};
+use orion_verity::{config, BLOCK_SIZE};
+
+// Global allocator for the server
+use orion_alloc::OrionHeap;
+
+#[global_allocator]
+static ALLOCATOR: OrionHeap = OrionHeap::empty();
+
+/// Memory backing the heap, holding the slot images being written and read back
+const HEAP_SIZE: usize = 4 * 1024 * 1024;
+static mut HEAP_ARENA: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+mod protocol;
+
+use protocol::*;
+
+const CLOCK_ID_MONOTONIC: u32 = 0;
+
+/// Time from the kernel start for init to confirm a boot
+const BOOT_CONFIRM_NS: u64 = 120 * 1_000_000_000;
+const POLL_INTERVAL_NS: u64 = 100 * 1_000_000;
+
+const IO_OP_LIST_DEVICES: u32 = 8;
+const IO_OP_TRUST_ANCHORS: u32 = 9;
+
+/// Audit event types emitted by the update server (user range, see capabilities.c)
+const AUDIT_UPDATE_COMMIT: u32 = 0x1501;
+const AUDIT_UPDATE_BOOT: u32 = 0x1502;
+const AUDIT_UPDATE_REJECTED: u32 = 0x1503;
+
+fn monotonic_ns() -> u64 {
+    clock_get(CLOCK_ID_MONOTONIC).unwrap_or(0)
+}
+
+/// Channel of the driver that owns the disk
+struct DriverIpc(IpcChannel);
+
+impl Transport for DriverIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+/// Whole disk through the raw disk requests of its driver
+struct BlockDisk {
+    client: DiskClient<DriverIpc>,
+    info: DiskInfo,
+}
+
+impl Disk for BlockDisk {
+    fn block_size(&self) -> u32 {
+        self.info.block_size
+    }
+
+    fn blocks(&self) -> u64 {
+        self.info.blocks
+    }
+
+    fn read(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), i32> {
+        self.client.read(lba, buffer)
+    }
+
+    fn write(&mut self, lba: u64, data: &[u8]) -> Result<(), i32> {
+        self.client.write(lba, data)
+    }
+
+    fn flush(&mut self) -> Result<(), i32> {
+        self.client.flush()
+    }
+}
+
+/// First disk holding a boot control record, asking the driver of every
+/// bound device
+fn find_system_disk() -> Option<(BlockDisk, SystemDisk)> {
+    let response = IpcChannel::connect("io").call(&IO_OP_LIST_DEVICES.to_le_bytes()).ok()?;
+    if response.len() < 4 || response[..4] != STATUS_OK.to_le_bytes() {
+        return None;
+    }
+    // handle, vendor, device, driver pid, then the driver name
+    let mut rest = &response[4..];
+    while rest.len() >= 24 {
+        let length = u32::from_le_bytes(rest[20..24].try_into().ok()?) as usize;
+        let name = core::str::from_utf8(rest.get(24..24 + length)?).ok()?;
+        rest = &rest[24 + length..];
+        if name.is_empty() {
+            continue;
+        }
+        let mut client = DiskClient::new(DriverIpc(IpcChannel::connect(name)));
+        let Ok(info) = client.info() else {
+            continue;
+        };
+        let mut disk = BlockDisk { client, info };
+        if let Ok(system) = SystemDisk::open(&mut disk) {
+            return Some((disk, system));
+        }
+    }
+    None
+}
+
+/// Keys system images may be signed with
+fn trust_anchors() -> Vec<[u8; PUBLIC_KEY_SIZE]> {
+    let Ok(reply) = IpcChannel::connect("io").call(&IO_OP_TRUST_ANCHORS.to_le_bytes()) else {
+        return Vec::new();
+    };
+    if reply.len() < 8 || reply[..4] != STATUS_OK.to_le_bytes() {
+        return Vec::new();
+    }
+    let count = u32::from_le_bytes(reply[4..8].try_into().unwrap()) as usize;
+    let keys = reply.get(8..8 + count * PUBLIC_KEY_SIZE).unwrap_or(&[]);
+    keys.chunks(PUBLIC_KEY_SIZE).map(|key| key.try_into().unwrap()).collect()
+}
+
+/// Check the boot configuration at the head of an image of `size` bytes
+fn check_image(head: &[u8], size: u64) -> i32 {
+    if head.len() < BLOCK_SIZE {
+        return STATUS_EINVAL;
+    }
+    match config::verify(&head[..BLOCK_SIZE], &trust_anchors()) {
+        Ok(config) if config.image_blocks() * BLOCK_SIZE as u64 == size => STATUS_OK,
+        Ok(_) => STATUS_EINVAL,
+        Err(_) => STATUS_EKEYREJECTED,
+    }
+}
+
+fn status_of(error: InstallError) -> i32 {
+    match error {
+        InstallError::TooSmall { .. } => STATUS_ENOSPC,
+        InstallError::NotFound => STATUS_ENOENT,
+        InstallError::Disk(status) | InstallError::Source(status) if status < 0 => status,
+        _ => STATUS_EIO,
+    }
+}
+
+struct Update {
+    writer: ImageWriter,
+    size: u64,
+}
+
+struct Installed {
+    disk: BlockDisk,
+    system: SystemDisk,
+    boot: u32,
+    update: Option<Update>,
+    committed: bool,
+}
+
+impl Installed {
+    /// STATUS reply payload, see protocol.rs
+    fn status(&self) -> Vec<u8> {
+        let record = &self.system.record;
+        let mut payload = Vec::new();
+        let deadline_ms = match self.boot {
+            BOOT_PENDING => BOOT_CONFIRM_NS.saturating_sub(monotonic_ns()) / 1_000_000,
+            _ => 0,
+        };
+        for value in [self.system.booted as u32, record.active_slot, self.boot] {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        payload.extend_from_slice(&deadline_ms.to_le_bytes());
+        for slot in record.slots.iter() {
+            payload.extend_from_slice(&slot.flags.to_le_bytes());
+            payload.extend_from_slice(&slot.tries.to_le_bytes());
+            payload.extend_from_slice(&slot.guid.0);
+        }
+        let (state, received, size) = match (&self.update, self.committed) {
+            (Some(update), _) => (UPDATE_RECEIVING, update.writer.written(), update.size),
+            (None, true) => (UPDATE_PENDING_REBOOT, 0, 0),
+            (None, false) => (UPDATE_IDLE, 0, 0),
+        };
+        payload.extend_from_slice(&state.to_le_bytes());
+        payload.extend_from_slice(&received.to_le_bytes());
+        payload.extend_from_slice(&size.to_le_bytes());
+        payload
+    }
+
+    fn confirm(&mut self, sender: u64) -> i32 {
+        match self.boot {
+            BOOT_GOOD | BOOT_CONFIRMED => STATUS_OK,
+            BOOT_FAILED => STATUS_ETIMEDOUT,
+            _ => {
+                let slot = self.system.booted;
+                let status = match self.system.confirm_boot(&mut self.disk) {
+                    Ok(_) => {
+                        self.boot = BOOT_CONFIRMED;
+                        STATUS_OK
+                    }
+                    Err(error) => status_of(error),
+                };
+                let record = format!("update-boot slot={} result=confirmed status={} sender={}", slot, status, sender);
+                let _ = audit_emit(AUDIT_UPDATE_BOOT, record.as_bytes());
+                status
+            }
+        }
+    }
+}
+
+struct UpdateServer {
+    /// None on a system not started from an installed disk
+    installed: Option<Installed>,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl UpdateServer {
+    fn new() -> Self {
+        let installed = find_system_disk().map(|(disk, system)| {
+            let slot = &system.record.slots[system.booted];
+            let boot = if slot.flags & SLOT_SUCCESSFUL != 0 { BOOT_GOOD } else { BOOT_PENDING };
+            Installed { disk, system, boot, update: None, committed: false }
+        });
+        Self {
+            installed,
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            self.check_deadline();
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    fn check_deadline(&mut self) {
+        let Some(installed) = self.installed.as_mut() else {
+            return;
+        };
+        if installed.boot != BOOT_PENDING || monotonic_ns() < BOOT_CONFIRM_NS {
+            return;
+        }
+        let slot = installed.system.booted;
+        let result = installed.system.fail_boot(&mut installed.disk);
+        installed.boot = BOOT_FAILED;
+        let record = match result {
+            Ok(()) => format!("update-boot slot={} result=timeout fallback={}", slot, 1 - slot),
+            // The bootloader still falls back once the tries are used up
+            Err(error) => format!("update-boot slot={} result=timeout status={}", slot, status_of(error)),
+        };
+        let _ = audit_emit(AUDIT_UPDATE_BOOT, record.as_bytes());
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        // A confirmation arriving after the deadline is too late even if
+        // the poll did not notice yet
+        self.check_deadline();
+        let Some(request) = UpdateRequest::decode(&message.data) else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+            return;
+        };
+
+        let rights = match request {
+            UpdateRequest::Status => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let Some(installed) = self.installed.as_mut() else {
+            self.ipc_channel.send(message.sender, &reply(STATUS_ENOENT, &[]));
+            return;
+        };
+
+        let mut payload = Vec::new();
+        let status = match request {
+            UpdateRequest::Status => {
+                payload = installed.status();
+                STATUS_OK
+            }
+            UpdateRequest::Begin { size } => {
+                if installed.update.is_some() || matches!(installed.boot, BOOT_PENDING | BOOT_FAILED) {
+                    STATUS_EBUSY
+                } else {
+                    match installed.system.begin_update(&mut installed.disk, size) {
+                        Ok(writer) => {
+                            installed.update = Some(Update { writer, size });
+                            installed.committed = false;
+                            payload.extend_from_slice(&(installed.system.inactive() as u32).to_le_bytes());
+                            STATUS_OK
+                        }
+                        Err(error) => status_of(error),
+                    }
+                }
+            }
+            UpdateRequest::Write { offset, data } => match installed.update.as_mut() {
+                None => STATUS_ENOENT,
+                Some(update) if offset != update.writer.written() => STATUS_ESPIPE,
+                Some(update) if offset + data.len() as u64 > update.size => STATUS_ENOSPC,
+                Some(update) if offset == 0 => match check_image(data, update.size) {
+                    STATUS_OK => match update.writer.write(&mut installed.disk, data) {
+                        Ok(()) => STATUS_OK,
+                        Err(error) => status_of(error),
+                    },
+                    status => {
+                        let record =
+                            format!("update-rejected size={} status={} sender={}", update.size, status, message.sender);
+                        let _ = audit_emit(AUDIT_UPDATE_REJECTED, record.as_bytes());
+                        status
+                    }
+                },
+                Some(update) => match update.writer.write(&mut installed.disk, data) {
+                    Ok(()) => STATUS_OK,
+                    Err(error) => status_of(error),
+                },
+            },
+            UpdateRequest::Commit { tries } => match installed.update.take() {
+                None => STATUS_ENOENT,
+                Some(update) if update.writer.written() != update.size => {
+                    installed.update = Some(update);
+                    STATUS_EINVAL
+                }
+                // A failed commit leaves the slot out of use; the update
+                // has to be sent again
+                Some(mut update) => {
+                    let slot = installed.system.inactive();
+                    let result =
+                        installed.system.commit_update(&mut installed.disk, &mut update.writer, tries, &mut |_| {});
+                    let status = match result {
+                        Ok(()) => {
+                            installed.committed = true;
+                            payload.extend_from_slice(&(slot as u32).to_le_bytes());
+                            STATUS_OK
+                        }
+                        Err(error) => status_of(error),
+                    };
+                    let record = format!(
+                        "update-commit slot={} size={} tries={} status={} sender={}",
+                        slot, update.size, tries, status, message.sender
+                    );
+                    let _ = audit_emit(AUDIT_UPDATE_COMMIT, record.as_bytes());
+                    status
+                }
+            },
+            UpdateRequest::Abort => match installed.update.take() {
+                Some(_) => STATUS_OK,
+                None => STATUS_ENOENT,
+            },
+            UpdateRequest::Confirm => installed.confirm(message.sender),
+        };
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    unsafe { ALLOCATOR.init(core::ptr::addr_of_mut!(HEAP_ARENA) as *mut u8, HEAP_SIZE) };
+    let mut server = UpdateServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
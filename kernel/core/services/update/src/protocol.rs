@@ -0,0 +1,147 @@
+/*
+ * Orion Operating System - Update Server Protocol
+ *
+ * IPC requests of the update server. All fields are little-endian; every
+ * message starts with a 32-bit opcode and every reply starts with a
+ * 32-bit signed status (0 or a negative errno).
+ *
+ *   STATUS   (none)               -> status record
+ *   BEGIN    size:u64             -> slot:u32
+ *   WRITE    offset:u64 data      -> (empty)
+ *   COMMIT   tries:u32            -> slot:u32
+ *   ABORT    (none)               -> (empty)
+ *   CONFIRM  (none)               -> (empty)
+ *
+ * An update is a system image sent with BEGIN, WRITE requests in order
+ * (`offset` is where the data goes in the image; anything else than the
+ * next byte is refused with ESPIPE) and COMMIT once all `size` bytes are
+ * in. COMMIT reads the slot back and makes it the one booted next, with
+ * `tries` boots to complete one (1 for 0). ABORT drops an update not yet
+ * committed. CONFIRM tells that the boot from the running slot completed:
+ * init sends it once the user interface is up; after the deadline it is
+ * refused with ETIMEDOUT and the next boot goes back to the other slot.
+ *
+ * The first WRITE has to carry the whole signed boot configuration, the
+ * first 4096 bytes of the image: one not signed by a trust anchor is
+ * refused with EKEYREJECTED, one describing an image of another size with
+ * EINVAL.
+ *
+ * The status record is booted:u32 active:u32 boot:u32 deadline_ms:u64,
+ * the two slots as flags:u32 tries:u32 guid[16], then update:u32
+ * received:u64 size:u64. `boot` is BOOT_*, `deadline_ms` the time left to
+ * confirm the boot (0 once decided), `update` is UPDATE_*. `data` is a
+ * `len: u32` followed by the bytes, at most MAX_WRITE.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+// Opcodes
+pub const OP_STATUS: u32 = 1;
+pub const OP_BEGIN: u32 = 2;
+pub const OP_WRITE: u32 = 3;
+pub const OP_COMMIT: u32 = 4;
+pub const OP_ABORT: u32 = 5;
+pub const OP_CONFIRM: u32 = 6;
+
+/// Largest WRITE payload
+pub const MAX_WRITE: usize = 60 * 1024;
+
+// Boot states
+/// The running slot had completed a boot before
+pub const BOOT_GOOD: u32 = 0;
+/// Waiting for init to confirm the boot
+pub const BOOT_PENDING: u32 = 1;
+pub const BOOT_CONFIRMED: u32 = 2;
+/// Not confirmed in time: the next boot starts the other slot
+pub const BOOT_FAILED: u32 = 3;
+
+// Update states
+pub const UPDATE_IDLE: u32 = 0;
+pub const UPDATE_RECEIVING: u32 = 1;
+/// Committed, booted on the next start
+pub const UPDATE_PENDING_REBOOT: u32 = 2;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+pub const STATUS_ENOSPC: i32 = -28;
+pub const STATUS_ESPIPE: i32 = -29;
+pub const STATUS_ETIMEDOUT: i32 = -110;
+pub const STATUS_EKEYREJECTED: i32 = -129;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UpdateRequest<'a> {
+    Status,
+    Begin { size: u64 },
+    Write { offset: u64, data: &'a [u8] },
+    Commit { tries: u32 },
+    Abort,
+    Confirm,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+impl<'a> UpdateRequest<'a> {
+    pub fn decode(data: &'a [u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_STATUS => Some(UpdateRequest::Status),
+            OP_BEGIN => Some(UpdateRequest::Begin { size: read_u64(data, 4)? }),
+            OP_WRITE => {
+                let offset = read_u64(data, 4)?;
+                let length = read_u32(data, 12)? as usize;
+                if length > MAX_WRITE {
+                    return None;
+                }
+                Some(UpdateRequest::Write { offset, data: data.get(16..16 + length)? })
+            }
+            OP_COMMIT => Some(UpdateRequest::Commit { tries: read_u32(data, 4)? }),
+            OP_ABORT => Some(UpdateRequest::Abort),
+            OP_CONFIRM => Some(UpdateRequest::Confirm),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_requests() {
+        let mut data = OP_WRITE.to_le_bytes().to_vec();
+        data.extend_from_slice(&4096u64.to_le_bytes());
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"abc");
+        assert_eq!(UpdateRequest::decode(&data), Some(UpdateRequest::Write { offset: 4096, data: b"abc" }));
+        assert_eq!(UpdateRequest::decode(&data[..data.len() - 1]), None);
+
+        let mut data = OP_BEGIN.to_le_bytes().to_vec();
+        data.extend_from_slice(&(1u64 << 32).to_le_bytes());
+        assert_eq!(UpdateRequest::decode(&data), Some(UpdateRequest::Begin { size: 1 << 32 }));
+        assert_eq!(UpdateRequest::decode(&OP_CONFIRM.to_le_bytes()), Some(UpdateRequest::Confirm));
+
+        let mut data = OP_WRITE.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&((MAX_WRITE + 1) as u32).to_le_bytes());
+        data.resize(16 + MAX_WRITE + 1, 0);
+        assert_eq!(UpdateRequest::decode(&data), None);
+        assert_eq!(UpdateRequest::decode(&99u32.to_le_bytes()), None);
+    }
+}
@@ -0,0 +1,510 @@
+/*
+ * Orion Operating System - VNC Server
+ *
+ * Remote framebuffer server: an output of the display server is streamed
+ * to RFB 3.8 viewers (see rfb.rs) with Raw or ZRLE encoding, and viewer
+ * pointer and key events are injected back as display input. Frames come
+ * from a capture stream into a shared buffer; the damaged rectangles are
+ * copied into a shadow framebuffer that every session encodes from at
+ * its own pace. TLS for VeNCrypt is terminated by the network server once
+ * a session asks for the upgrade.
+ *
+ * The server is idle until an administrator sends START (see
+ * protocol.rs); the listener and the capture stream are then polled
+ * between IPC checks.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use orion_cap::Capability;
+use orion_http::socket::{NetChannel, NetListener, NetStream};
+use orion_http::{IoError, Listener};
+use orion_ipc::{IpcChannel, IpcMessage};
+use orion_proto::reply;
+use orion_proto::rights::{CAP_ADMIN, CAP_READ};
+
+// Global allocator for the server
+use linked_list_allocator::LockedHeap;
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+mod pixel;
+mod protocol;
+mod rfb;
+mod zrle;
+
+use protocol::*;
+use rfb::{Area, Framebuffer, Input, Password, RfbTransport, Security, Session, SessionStats, PASSWORD_SALT_SIZE};
+
+/// Pause between two polls of the sessions
+const POLL_INTERVAL_NS: u64 = 5_000_000;
+
+const LISTEN_BACKLOG: u16 = 4;
+const MAX_SESSIONS: usize = 8;
+
+/// Desktop name announced in ServerInit
+const DESKTOP_NAME: &str = "Orion";
+
+/// Entropy GET_RANDOM request opcode (see services/entropy/src/protocol.rs)
+const ENTROPY_OP_GET_RANDOM: u32 = 1;
+
+// Display server requests and events (see services/display/src/protocol.rs)
+const DISPLAY_OP_CAPTURE_START: u32 = 8;
+const DISPLAY_OP_CAPTURE_RELEASE: u32 = 9;
+const DISPLAY_OP_CAPTURE_STOP: u32 = 10;
+const DISPLAY_OP_LIST_OUTPUTS: u32 = 11;
+const DISPLAY_OP_INPUT_POINTER: u32 = 13;
+const DISPLAY_OP_INPUT_KEY: u32 = 14;
+const DISPLAY_EVENT_CAPTURE_FRAME: u32 = 0x8001;
+const DISPLAY_EVENT_CAPTURE_ENDED: u32 = 0x8002;
+const DISPLAY_TARGET_OUTPUT: u32 = 1;
+const DISPLAY_CAPTURE_CURSOR: u32 = 1 << 0;
+
+// Capture buffer layout (see services/display/src/capture.rs)
+const FRAME_MAGIC: u32 = 0x5041_434F;
+const FRAME_RECTS_OFFSET: usize = 40;
+const FRAME_MAX_RECTS: usize = 16;
+const FRAME_PIXELS_OFFSET: usize = 4096;
+
+/// IPC channel to the network server used by the listener
+struct NetIpc(IpcChannel);
+
+impl NetChannel for NetIpc {
+    fn call(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        self.0.call(request).ok()
+    }
+}
+
+impl RfbTransport for NetStream<NetIpc> {
+    fn start_tls(&mut self, certificate_handle: u64, key_handle: u64) -> Result<(), i32> {
+        NetStream::start_tls(self, certificate_handle, key_handle)
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Capture stream of the served output and its shared buffer
+struct Capture {
+    stream: u32,
+    mapping: u64,
+    size: usize,
+}
+
+struct Running {
+    listener: NetListener<NetIpc>,
+    output: u32,
+    flags: u16,
+    max_fps: u32,
+    security: Security,
+    capture: Option<Capture>,
+}
+
+struct VncServer {
+    running: Option<Running>,
+    sessions: Vec<Session<NetStream<NetIpc>>>,
+    framebuffer: Framebuffer,
+    /// Counters of sessions already closed
+    closed: SessionStats,
+    frames: u64,
+    display: IpcChannel,
+    entropy: IpcChannel,
+    ipc_channel: IpcChannel,
+    capabilities: Capability,
+}
+
+impl VncServer {
+    fn new() -> Self {
+        Self {
+            running: None,
+            sessions: Vec::new(),
+            framebuffer: Framebuffer::new(0, 0),
+            closed: SessionStats::default(),
+            frames: 0,
+            display: IpcChannel::connect("display"),
+            entropy: IpcChannel::connect("entropy"),
+            ipc_channel: IpcChannel::new(),
+            capabilities: Capability::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            while let Some(message) = self.ipc_channel.receive() {
+                self.handle_message(message);
+            }
+            if self.running.is_some() {
+                self.poll_sessions();
+            }
+            let _ = orion_sys::nanosleep(POLL_INTERVAL_NS);
+        }
+    }
+
+    /// Issue a display server request and split the reply
+    fn display_call(&mut self, request: &[u8]) -> Result<Vec<u8>, i32> {
+        let response = self.display.call(request).map_err(|_| STATUS_EIO)?;
+        match read_u32(&response, 0).map(|status| status as i32) {
+            Some(STATUS_OK) => Ok(response[4..].to_vec()),
+            Some(status) => Err(status),
+            None => Err(STATUS_EIO),
+        }
+    }
+
+    /// Current size of `output`
+    fn output_size(&mut self, output: u32) -> Result<(u16, u16), i32> {
+        let records = self.display_call(&DISPLAY_OP_LIST_OUTPUTS.to_le_bytes())?;
+        let record =
+            records.chunks_exact(12).find(|record| read_u32(record, 0) == Some(output)).ok_or(STATUS_ENOENT)?;
+        let width = u16::try_from(read_u32(record, 4).unwrap_or(0)).map_err(|_| STATUS_EINVAL)?;
+        let height = u16::try_from(read_u32(record, 8).unwrap_or(0)).map_err(|_| STATUS_EINVAL)?;
+        Ok((width, height))
+    }
+
+    /// Capture `output` into a fresh buffer sized for `width` x `height`
+    fn start_capture(&mut self, output: u32, max_fps: u32, width: u16, height: u16) -> Result<Capture, i32> {
+        let size = FRAME_PIXELS_OFFSET + width as usize * height as usize * 4;
+        let buffer = orion_sys::shm_create(size, 0).map_err(|_| STATUS_EIO)?;
+        let (mapping, size) = orion_sys::shm_attach(buffer, 0, 0).map_err(|_| STATUS_EIO)?;
+
+        let mut request = Vec::with_capacity(28);
+        for value in [DISPLAY_OP_CAPTURE_START, DISPLAY_TARGET_OUTPUT, output, DISPLAY_CAPTURE_CURSOR, max_fps] {
+            request.extend_from_slice(&value.to_le_bytes());
+        }
+        request.extend_from_slice(&buffer.to_le_bytes());
+        match self.display_call(&request).and_then(|payload| read_u32(&payload, 0).ok_or(STATUS_EIO)) {
+            Ok(stream) => Ok(Capture { stream, mapping, size }),
+            Err(status) => {
+                let _ = orion_sys::shm_detach(mapping);
+                Err(status)
+            }
+        }
+    }
+
+    fn stop_capture(&mut self, capture: Capture) {
+        let mut request = Vec::with_capacity(8);
+        request.extend_from_slice(&DISPLAY_OP_CAPTURE_STOP.to_le_bytes());
+        request.extend_from_slice(&capture.stream.to_le_bytes());
+        let _ = self.display_call(&request);
+        let _ = orion_sys::shm_detach(capture.mapping);
+    }
+
+    /// Draw a password salt from the entropy service
+    fn random_salt(&mut self) -> Option<[u8; PASSWORD_SALT_SIZE]> {
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&ENTROPY_OP_GET_RANDOM.to_le_bytes());
+        request.extend_from_slice(&(PASSWORD_SALT_SIZE as u32).to_le_bytes());
+        request.extend_from_slice(&0u32.to_le_bytes());
+
+        let response = self.entropy.call(&request).ok()?;
+        if response.len() < 4 + PASSWORD_SALT_SIZE || response[..4] != STATUS_OK.to_le_bytes() {
+            return None;
+        }
+        let mut salt = [0u8; PASSWORD_SALT_SIZE];
+        salt.copy_from_slice(&response[4..4 + PASSWORD_SALT_SIZE]);
+        Some(salt)
+    }
+
+    fn start(&mut self, config: StartConfig) -> i32 {
+        if self.running.is_some() {
+            return STATUS_EBUSY;
+        }
+        let tls = config.flags & FLAG_TLS != 0;
+        let allow_none = config.flags & FLAG_ALLOW_NONE != 0;
+        let has_password = !config.password.is_empty();
+        // A password is only ever checked inside TLS, and viewers need at
+        // least one way in
+        if (has_password && !tls) || !(allow_none || has_password) {
+            return STATUS_EINVAL;
+        }
+        let password = if has_password {
+            match self.random_salt() {
+                Some(salt) => Some(Password::new(salt, config.password.as_bytes())),
+                None => return STATUS_EIO,
+            }
+        } else {
+            None
+        };
+        let security =
+            Security { allow_none, tls: tls.then_some((config.certificate_handle, config.key_handle)), password };
+
+        let (width, height) = match self.output_size(config.output) {
+            Ok(size) => size,
+            Err(status) => return status,
+        };
+        let listener = match NetListener::bind(NetIpc(IpcChannel::connect("net")), 0, config.port, LISTEN_BACKLOG) {
+            Ok(listener) => listener,
+            Err(status) => return status,
+        };
+        let capture = match self.start_capture(config.output, config.max_fps, width, height) {
+            Ok(capture) => capture,
+            Err(status) => return status,
+        };
+        self.framebuffer = Framebuffer::new(width, height);
+        self.running = Some(Running {
+            listener,
+            output: config.output,
+            flags: config.flags,
+            max_fps: config.max_fps,
+            security,
+            capture: Some(capture),
+        });
+        STATUS_OK
+    }
+
+    fn stop(&mut self) -> i32 {
+        let Some(mut running) = self.running.take() else {
+            return STATUS_ENOENT;
+        };
+        if let Some(capture) = running.capture.take() {
+            self.stop_capture(capture);
+        }
+        for mut session in self.sessions.drain(..) {
+            session.close();
+            self.closed.updates += session.stats.updates;
+            self.closed.bytes_sent += session.stats.bytes_sent;
+        }
+        STATUS_OK
+    }
+
+    /// Copy a captured frame into the shadow framebuffer and hand its
+    /// buffer back
+    fn frame_ready(&mut self, stream: u32) {
+        let Some(capture) = self.running.as_ref().and_then(|running| running.capture.as_ref()) else {
+            return;
+        };
+        if capture.stream != stream {
+            return;
+        }
+        // The display server leaves the buffer alone until it is released
+        let buffer = unsafe { core::slice::from_raw_parts(capture.mapping as *const u8, capture.size) };
+        let header = &buffer[..FRAME_PIXELS_OFFSET];
+        let pixels = unsafe {
+            core::slice::from_raw_parts(
+                (capture.mapping + FRAME_PIXELS_OFFSET as u64) as *const u32,
+                (capture.size - FRAME_PIXELS_OFFSET) / 4,
+            )
+        };
+
+        if read_u32(header, 0) == Some(FRAME_MAGIC) {
+            let width = read_u32(header, 4).unwrap_or(0) as u16;
+            let height = read_u32(header, 8).unwrap_or(0) as u16;
+            if width != self.framebuffer.width || height != self.framebuffer.height {
+                self.resize(width, height);
+            }
+            let count = (read_u32(header, 32).unwrap_or(0) as usize).min(FRAME_MAX_RECTS);
+            for index in 0..count {
+                let offset = FRAME_RECTS_OFFSET + index * 16;
+                let x = read_u32(header, offset).unwrap_or(0).min(width as u32) as u16;
+                let y = read_u32(header, offset + 4).unwrap_or(0).min(height as u32) as u16;
+                let area_width = read_u32(header, offset + 8).unwrap_or(0).min((width - x) as u32) as u16;
+                let area_height = read_u32(header, offset + 12).unwrap_or(0).min((height - y) as u32) as u16;
+                let stride = width as usize;
+                for row in y as usize..y as usize + area_height as usize {
+                    let start = row * stride + x as usize;
+                    let end = start + area_width as usize;
+                    if end <= pixels.len() {
+                        self.framebuffer.pixels[start..end].copy_from_slice(&pixels[start..end]);
+                    }
+                }
+                let area = Area::new(x, y, area_width, area_height);
+                for session in self.sessions.iter_mut() {
+                    session.damage(area);
+                }
+            }
+            self.frames += 1;
+        }
+
+        let mut request = Vec::with_capacity(8);
+        request.extend_from_slice(&DISPLAY_OP_CAPTURE_RELEASE.to_le_bytes());
+        request.extend_from_slice(&stream.to_le_bytes());
+        let _ = self.display_call(&request);
+    }
+
+    /// The output changed size: viewers without DesktopSize support are
+    /// disconnected, the others are told on their next update
+    fn resize(&mut self, width: u16, height: u16) {
+        self.framebuffer = Framebuffer::new(width, height);
+        for session in self.sessions.iter_mut() {
+            if session.supports_desktop_size() {
+                session.resize();
+            } else {
+                session.close();
+            }
+        }
+    }
+
+    /// The capture stream ended, usually because the output outgrew the
+    /// buffer; capture again at the new size or shut down
+    fn capture_ended(&mut self, stream: u32) {
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+        match running.capture.take() {
+            Some(capture) if capture.stream == stream => {
+                let _ = orion_sys::shm_detach(capture.mapping);
+            }
+            other => {
+                running.capture = other;
+                return;
+            }
+        }
+        let (output, max_fps) = (running.output, running.max_fps);
+        let restarted =
+            self.output_size(output).and_then(|(width, height)| self.start_capture(output, max_fps, width, height));
+        match restarted {
+            Ok(capture) => {
+                if let Some(running) = self.running.as_mut() {
+                    running.capture = Some(capture);
+                }
+            }
+            Err(_) => {
+                self.stop();
+            }
+        }
+    }
+
+    fn forward_input(&mut self, output: u32, input: &[Input]) {
+        for event in input {
+            let mut request = Vec::with_capacity(20);
+            match *event {
+                Input::Pointer { x, y, buttons } => {
+                    for value in [DISPLAY_OP_INPUT_POINTER, output, x as u32, y as u32, buttons as u32] {
+                        request.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                Input::Key { keysym, down } => {
+                    for value in [DISPLAY_OP_INPUT_KEY, keysym, down as u32] {
+                        request.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+            }
+            let _ = self.display_call(&request);
+        }
+    }
+
+    fn poll_sessions(&mut self) {
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+        loop {
+            match running.listener.accept() {
+                Ok(stream) if self.sessions.len() < MAX_SESSIONS => {
+                    self.sessions.push(Session::new(stream, running.security, DESKTOP_NAME));
+                }
+                // Dropping the stream closes the connection
+                Ok(_) => {}
+                Err(IoError::WouldBlock) | Err(IoError::Closed) => break,
+            }
+        }
+        let (output, view_only) = (running.output, running.flags & FLAG_VIEW_ONLY != 0);
+
+        let mut input = Vec::new();
+        for session in self.sessions.iter_mut() {
+            session.poll(&self.framebuffer, &mut input);
+        }
+        let closed = &mut self.closed;
+        self.sessions.retain(|session| {
+            if session.is_closed() {
+                closed.updates += session.stats.updates;
+                closed.bytes_sent += session.stats.bytes_sent;
+            }
+            !session.is_closed()
+        });
+        if !view_only {
+            self.forward_input(output, &input);
+        }
+    }
+
+    fn handle_event(&mut self, data: &[u8]) {
+        let Some(stream) = read_u32(data, 4) else {
+            return;
+        };
+        match read_u32(data, 0) {
+            Some(DISPLAY_EVENT_CAPTURE_FRAME) if read_u64(data, 8).is_some() => self.frame_ready(stream),
+            Some(DISPLAY_EVENT_CAPTURE_ENDED) => self.capture_ended(stream),
+            _ => {}
+        }
+    }
+
+    fn handle_message(&mut self, message: IpcMessage) {
+        // Capture events of the display server carry no reply
+        if matches!(read_u32(&message.data, 0), Some(DISPLAY_EVENT_CAPTURE_FRAME | DISPLAY_EVENT_CAPTURE_ENDED)) {
+            self.handle_event(&message.data);
+            return;
+        }
+        let request = match VncRequest::decode(&message.data) {
+            Some(request) => request,
+            None => {
+                self.ipc_channel.send(message.sender, &reply(STATUS_EINVAL, &[]));
+                return;
+            }
+        };
+
+        let rights = match request {
+            VncRequest::Status => CAP_READ,
+            _ => CAP_ADMIN,
+        };
+        if !self.capabilities.check_rights(message.capability, rights, message.sender) {
+            self.ipc_channel.send(message.sender, &reply(STATUS_EPERM, &[]));
+            return;
+        }
+
+        let mut payload = Vec::new();
+        let status = match request {
+            VncRequest::Start(config) => self.start(config),
+            VncRequest::Stop => self.stop(),
+            VncRequest::Status => {
+                let (mut updates, mut bytes) = (self.closed.updates, self.closed.bytes_sent);
+                for session in self.sessions.iter() {
+                    updates += session.stats.updates;
+                    bytes += session.stats.bytes_sent;
+                }
+                for value in [self.sessions.len() as u32, self.framebuffer.width as u32, self.framebuffer.height as u32]
+                {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                for value in [self.frames, updates, bytes] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+                STATUS_OK
+            }
+        };
+
+        self.ipc_channel.send(message.sender, &reply(status, &payload));
+    }
+}
+
+fn main() {
+    let mut server = VncServer::new();
+    server.run();
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * Orion Operating System - RFB Pixel Formats
+ *
+ * Conversion of the display server's 32-bit xRGB pixels into the format a
+ * viewer asked for with SetPixelFormat. Only true-colour formats of 8, 16
+ * or 32 bits per pixel are accepted; colour maps are not supported.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+pub const PIXEL_FORMAT_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    pub bits_per_pixel: u8,
+    pub depth: u8,
+    pub big_endian: bool,
+    pub red_max: u16,
+    pub green_max: u16,
+    pub blue_max: u16,
+    pub red_shift: u8,
+    pub green_shift: u8,
+    pub blue_shift: u8,
+}
+
+impl PixelFormat {
+    /// Native format of the display server, advertised in ServerInit
+    pub const SERVER: PixelFormat = PixelFormat {
+        bits_per_pixel: 32,
+        depth: 24,
+        big_endian: false,
+        red_max: 255,
+        green_max: 255,
+        blue_max: 255,
+        red_shift: 16,
+        green_shift: 8,
+        blue_shift: 0,
+    };
+
+    /// Parse a PIXEL_FORMAT; None for colour-mapped or unusable formats
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let data = data.get(..PIXEL_FORMAT_SIZE)?;
+        let format = PixelFormat {
+            bits_per_pixel: data[0],
+            depth: data[1],
+            big_endian: data[2] != 0,
+            red_max: u16::from_be_bytes([data[4], data[5]]),
+            green_max: u16::from_be_bytes([data[6], data[7]]),
+            blue_max: u16::from_be_bytes([data[8], data[9]]),
+            red_shift: data[10],
+            green_shift: data[11],
+            blue_shift: data[12],
+        };
+        let true_colour = data[3] != 0;
+        let bits = format.bits_per_pixel as u32;
+        let fits = |max: u16, shift: u8| shift as u32 + (16 - max.leading_zeros()) <= bits;
+        if !true_colour
+            || !matches!(format.bits_per_pixel, 8 | 16 | 32)
+            || !fits(format.red_max, format.red_shift)
+            || !fits(format.green_max, format.green_shift)
+            || !fits(format.blue_max, format.blue_shift)
+        {
+            return None;
+        }
+        Some(format)
+    }
+
+    pub fn encode(&self) -> [u8; PIXEL_FORMAT_SIZE] {
+        let mut out = [0u8; PIXEL_FORMAT_SIZE];
+        out[0] = self.bits_per_pixel;
+        out[1] = self.depth;
+        out[2] = self.big_endian as u8;
+        out[3] = 1; // True colour
+        out[4..6].copy_from_slice(&self.red_max.to_be_bytes());
+        out[6..8].copy_from_slice(&self.green_max.to_be_bytes());
+        out[8..10].copy_from_slice(&self.blue_max.to_be_bytes());
+        out[10] = self.red_shift;
+        out[11] = self.green_shift;
+        out[12] = self.blue_shift;
+        out
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bits_per_pixel as usize / 8
+    }
+
+    /// Pixel value of an xRGB pixel
+    pub fn convert(&self, xrgb: u32) -> u32 {
+        let scale = |value: u32, max: u16| (value * max as u32 + 127) / 255;
+        (scale((xrgb >> 16) & 0xff, self.red_max) << self.red_shift)
+            | (scale((xrgb >> 8) & 0xff, self.green_max) << self.green_shift)
+            | (scale(xrgb & 0xff, self.blue_max) << self.blue_shift)
+    }
+
+    /// Append a converted pixel value as PIXEL
+    pub fn put(&self, value: u32, out: &mut Vec<u8>) {
+        match (self.bits_per_pixel, self.big_endian) {
+            (8, _) => out.push(value as u8),
+            (16, false) => out.extend_from_slice(&(value as u16).to_le_bytes()),
+            (16, true) => out.extend_from_slice(&(value as u16).to_be_bytes()),
+            (_, false) => out.extend_from_slice(&value.to_le_bytes()),
+            (_, true) => out.extend_from_slice(&value.to_be_bytes()),
+        }
+    }
+
+    // Which 3 bytes of a 32-bit pixel carry colour, when only 3 do
+    fn compact_bytes(&self) -> Option<bool> {
+        if self.bits_per_pixel != 32 || self.depth > 24 {
+            return None;
+        }
+        let mask = ((self.red_max as u32) << self.red_shift)
+            | ((self.green_max as u32) << self.green_shift)
+            | ((self.blue_max as u32) << self.blue_shift);
+        if mask <= 0x00ff_ffff {
+            Some(false)
+        } else if mask & 0xff == 0 {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Size of a ZRLE CPIXEL
+    pub fn cpixel_size(&self) -> usize {
+        if self.compact_bytes().is_some() {
+            3
+        } else {
+            self.bytes_per_pixel()
+        }
+    }
+
+    /// Append a converted pixel value as CPIXEL
+    pub fn put_cpixel(&self, value: u32, out: &mut Vec<u8>) {
+        let bytes = if self.big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        match (self.compact_bytes(), self.big_endian) {
+            (Some(false), false) | (Some(true), true) => out.extend_from_slice(&bytes[..3]),
+            (Some(true), false) | (Some(false), true) => out.extend_from_slice(&bytes[1..]),
+            (None, _) => self.put(value, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_converts_pixels() {
+        let server = PixelFormat::SERVER;
+        assert_eq!(PixelFormat::parse(&server.encode()), Some(server));
+        assert_eq!(server.convert(0xff12_3456), 0x12_3456);
+
+        // RGB565 big endian
+        let rgb565 = PixelFormat {
+            bits_per_pixel: 16,
+            depth: 16,
+            big_endian: true,
+            red_max: 31,
+            green_max: 63,
+            blue_max: 31,
+            red_shift: 11,
+            green_shift: 5,
+            blue_shift: 0,
+        };
+        let value = rgb565.convert(0x00ff_8000);
+        assert_eq!(value, (31 << 11) | (32 << 5));
+        let mut out = Vec::new();
+        rgb565.put(value, &mut out);
+        assert_eq!(out, value.to_be_bytes()[2..]);
+        assert_eq!(rgb565.cpixel_size(), 2);
+
+        let mut encoded = rgb565.encode();
+        encoded[3] = 0;
+        assert_eq!(PixelFormat::parse(&encoded), None);
+        encoded[3] = 1;
+        encoded[10] = 12;
+        assert_eq!(PixelFormat::parse(&encoded), None);
+    }
+
+    #[test]
+    fn test_packs_cpixels() {
+        let mut out = Vec::new();
+        PixelFormat::SERVER.put_cpixel(0x12_3456, &mut out);
+        assert_eq!(out, [0x56, 0x34, 0x12]);
+
+        // Colour in the top three bytes of a big-endian pixel
+        let high =
+            PixelFormat { big_endian: true, red_shift: 24, green_shift: 16, blue_shift: 8, ..PixelFormat::SERVER };
+        out.clear();
+        high.put_cpixel(high.convert(0x12_3456), &mut out);
+        assert_eq!(out, vec![0x12, 0x34, 0x56]);
+    }
+}
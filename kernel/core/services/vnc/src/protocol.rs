@@ -0,0 +1,157 @@
+/*
+ * Orion Operating System - VNC Server Protocol
+ *
+ * Administrative IPC requests of the VNC server. All fields are
+ * little-endian; every message starts with a 32-bit opcode and every
+ * reply starts with a 32-bit signed status (0 or a negative errno).
+ *
+ *   START   output:u32 port:u16 flags:u16 max_fps:u32
+ *           cert:u64 key:u64 password     -> (empty)
+ *   STOP    (none)                        -> (empty)
+ *   STATUS  (none)                        -> sessions:u32 width:u32
+ *                                            height:u32 frames:u64
+ *                                            updates:u64 bytes:u64
+ *
+ * START captures `output` and listens on `port` (0 for 5900). cert and
+ * key are keyring handles used for VeNCrypt when FLAG_TLS is set; an
+ * empty password disables X509Plain, and FLAG_ALLOW_NONE lets viewers in
+ * without authentication. FLAG_VIEW_ONLY drops all viewer input.
+ *
+ * Strings are a `len: u32` followed by UTF-8 bytes.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Opcodes
+pub const OP_START: u32 = 1;
+pub const OP_STOP: u32 = 2;
+pub const OP_STATUS: u32 = 3;
+
+// START flags
+pub const FLAG_ALLOW_NONE: u16 = 1 << 0;
+pub const FLAG_VIEW_ONLY: u16 = 1 << 1;
+pub const FLAG_TLS: u16 = 1 << 2;
+
+pub const DEFAULT_PORT: u16 = 5900;
+
+/// Longest accepted password
+pub const MAX_PASSWORD: usize = 256;
+
+// Reply status codes
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_EPERM: i32 = -1;
+pub const STATUS_ENOENT: i32 = -2;
+pub const STATUS_EIO: i32 = -5;
+pub const STATUS_EBUSY: i32 = -16;
+pub const STATUS_EINVAL: i32 = -22;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StartConfig {
+    pub output: u32,
+    pub port: u16,
+    pub flags: u16,
+    pub max_fps: u32,
+    pub certificate_handle: u64,
+    pub key_handle: u64,
+    pub password: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VncRequest {
+    Start(StartConfig),
+    Stop,
+    Status,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(raw))
+}
+
+/// Decode a `len, utf-8 bytes` field
+fn read_string(data: &[u8], offset: usize) -> Option<String> {
+    let len = read_u32(data, offset)? as usize;
+    let bytes = data.get(offset + 4..offset + 4 + len)?;
+    Some(String::from(core::str::from_utf8(bytes).ok()?))
+}
+
+impl VncRequest {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            OP_START => {
+                let password = read_string(data, 32)?;
+                if password.len() > MAX_PASSWORD {
+                    return None;
+                }
+                let port = match read_u16(data, 8)? {
+                    0 => DEFAULT_PORT,
+                    port => port,
+                };
+                Some(VncRequest::Start(StartConfig {
+                    output: read_u32(data, 4)?,
+                    port,
+                    flags: read_u16(data, 10)?,
+                    max_fps: read_u32(data, 12)?,
+                    certificate_handle: read_u64(data, 16)?,
+                    key_handle: read_u64(data, 24)?,
+                    password,
+                }))
+            }
+            OP_STOP => Some(VncRequest::Stop),
+            OP_STATUS => Some(VncRequest::Status),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_start() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&OP_START.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&(FLAG_TLS | FLAG_VIEW_ONLY).to_le_bytes());
+        data.extend_from_slice(&30u32.to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&8u64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"pw");
+
+        let expected = StartConfig {
+            output: 2,
+            port: DEFAULT_PORT,
+            flags: FLAG_TLS | FLAG_VIEW_ONLY,
+            max_fps: 30,
+            certificate_handle: 7,
+            key_handle: 8,
+            password: String::from("pw"),
+        };
+        assert_eq!(VncRequest::decode(&data), Some(VncRequest::Start(expected)));
+        assert_eq!(VncRequest::decode(&data[..33]), None);
+    }
+}
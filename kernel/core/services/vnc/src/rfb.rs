@@ -0,0 +1,775 @@
+/*
+ * Orion Operating System - RFB Sessions
+ *
+ * One viewer connection speaking RFB 3.8: version and security handshake,
+ * ClientInit/ServerInit, then client messages in and FramebufferUpdates
+ * out. Security is None (when allowed) or VeNCrypt, whose X509 subtypes
+ * upgrade the stream to TLS in the network server before the password of
+ * X509Plain crosses the wire. Passwords are only kept as salted SHA-512
+ * digests.
+ *
+ * Sessions are polled: everything read is buffered until a whole message
+ * is there, and updates are only produced while the viewer has one
+ * requested and the output queue is below OUTPUT_HIGH_WATER, so a slow
+ * viewer gets fewer, larger updates instead of an ever-growing backlog.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+use alloc::vec::Vec;
+
+use orion_crypto::sha512::{Sha512, SHA512_DIGEST_SIZE};
+use orion_http::{IoError, Transport};
+
+use crate::pixel::{PixelFormat, PIXEL_FORMAT_SIZE};
+use crate::zrle::{self, ZlibStream};
+
+const PROTOCOL_VERSION: &[u8; 12] = b"RFB 003.008\n";
+
+// Security types
+pub const SECURITY_NONE: u8 = 1;
+pub const SECURITY_VENCRYPT: u8 = 19;
+
+// VeNCrypt subtypes
+pub const VENCRYPT_X509_NONE: u32 = 260;
+pub const VENCRYPT_X509_PLAIN: u32 = 262;
+
+// Client message types
+const MSG_SET_PIXEL_FORMAT: u8 = 0;
+const MSG_SET_ENCODINGS: u8 = 2;
+const MSG_UPDATE_REQUEST: u8 = 3;
+const MSG_KEY_EVENT: u8 = 4;
+const MSG_POINTER_EVENT: u8 = 5;
+const MSG_CLIENT_CUT_TEXT: u8 = 6;
+
+// Server message types
+const MSG_FRAMEBUFFER_UPDATE: u8 = 0;
+
+// Encodings
+pub const ENCODING_RAW: i32 = 0;
+pub const ENCODING_ZRLE: i32 = 16;
+pub const ENCODING_DESKTOP_SIZE: i32 = -223;
+
+/// Rectangles tracked per session before collapsing to a bounding box
+const MAX_DIRTY_RECTS: usize = 16;
+
+/// Queued output above which no new update is produced
+pub const OUTPUT_HIGH_WATER: usize = 256 * 1024;
+
+/// Buffered input above which the viewer is dropped
+const MAX_INBOUND: usize = 64 * 1024;
+
+/// Bytes requested from the transport per read
+const READ_SIZE: usize = 4096;
+
+/// Longest username or password of X509Plain
+const MAX_CREDENTIAL: usize = 256;
+
+pub const PASSWORD_SALT_SIZE: usize = 16;
+
+/// Password stored as SHA-512(salt || password)
+#[derive(Debug, Clone, Copy)]
+pub struct Password {
+    salt: [u8; PASSWORD_SALT_SIZE],
+    digest: [u8; SHA512_DIGEST_SIZE],
+}
+
+impl Password {
+    pub fn new(salt: [u8; PASSWORD_SALT_SIZE], password: &[u8]) -> Self {
+        Self { salt, digest: salted_digest(&salt, password) }
+    }
+
+    pub fn verify(&self, password: &[u8]) -> bool {
+        let digest = salted_digest(&self.salt, password);
+        let mut difference = 0u8;
+        for (x, y) in digest.iter().zip(self.digest.iter()) {
+            difference |= x ^ y;
+        }
+        difference == 0
+    }
+}
+
+fn salted_digest(salt: &[u8], password: &[u8]) -> [u8; SHA512_DIGEST_SIZE] {
+    let mut hash = Sha512::new();
+    hash.update(salt);
+    hash.update(password);
+    hash.finalize()
+}
+
+/// What a viewer may authenticate with
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Security {
+    /// Offer security type None (and VeNCrypt X509None with TLS)
+    pub allow_none: bool,
+    /// Keyring handles of the certificate and key for VeNCrypt
+    pub tls: Option<(u64, u64)>,
+    /// Required by VeNCrypt X509Plain; the username is not checked
+    pub password: Option<Password>,
+}
+
+impl Security {
+    fn types(&self) -> Vec<u8> {
+        let mut types = Vec::new();
+        if !self.vencrypt_subtypes().is_empty() {
+            types.push(SECURITY_VENCRYPT);
+        }
+        if self.allow_none {
+            types.push(SECURITY_NONE);
+        }
+        types
+    }
+
+    fn vencrypt_subtypes(&self) -> Vec<u32> {
+        let mut subtypes = Vec::new();
+        if self.tls.is_some() {
+            if self.password.is_some() {
+                subtypes.push(VENCRYPT_X509_PLAIN);
+            }
+            if self.allow_none {
+                subtypes.push(VENCRYPT_X509_NONE);
+            }
+        }
+        subtypes
+    }
+}
+
+/// Shadow copy of the captured output, in xRGB
+pub struct Framebuffer {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self { width, height, pixels: alloc::vec![0; width as usize * height as usize] }
+    }
+}
+
+/// Viewer input to forward to the display server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Pointer { x: u16, y: u16, buttons: u8 },
+    Key { keysym: u32, down: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Area {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self { x, y, width, height }
+    }
+
+    fn right(&self) -> u32 {
+        self.x as u32 + self.width as u32
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y as u32 + self.height as u32
+    }
+
+    fn clip(&self, width: u16, height: u16) -> Option<Area> {
+        let right = self.right().min(width as u32);
+        let bottom = self.bottom().min(height as u32);
+        if right <= self.x as u32 || bottom <= self.y as u32 {
+            return None;
+        }
+        Some(Area::new(self.x, self.y, (right - self.x as u32) as u16, (bottom - self.y as u32) as u16))
+    }
+
+    fn touches(&self, other: &Area) -> bool {
+        self.x as u32 <= other.right()
+            && other.x as u32 <= self.right()
+            && self.y as u32 <= other.bottom()
+            && other.y as u32 <= self.bottom()
+    }
+
+    fn union(&self, other: &Area) -> Area {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+        Area::new(x, y, (right - x as u32) as u16, (bottom - y as u32) as u16)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Version,
+    SecurityType,
+    VencryptVersion,
+    VencryptSubtype,
+    PlainAuth,
+    /// X509None: SecurityResult follows the TLS handshake
+    TlsSecured,
+    ClientInit,
+    Normal,
+    /// Waiting for queued output to drain before closing
+    Closing,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionStats {
+    pub updates: u64,
+    pub bytes_sent: u64,
+}
+
+pub struct Session<T: RfbTransport> {
+    transport: T,
+    security: Security,
+    state: State,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+    /// Start TLS once the subtype acknowledgement has been written
+    tls_pending: bool,
+    format: PixelFormat,
+    zrle: bool,
+    desktop_size: bool,
+    zlib: ZlibStream,
+    dirty: Vec<Area>,
+    update_requested: bool,
+    resize_pending: bool,
+    /// ClientCutText bytes still to be discarded
+    skip: usize,
+    name: &'static str,
+    pub stats: SessionStats,
+}
+
+/// Transport able to switch to TLS mid-stream
+pub trait RfbTransport: Transport {
+    fn start_tls(&mut self, certificate_handle: u64, key_handle: u64) -> Result<(), i32>;
+}
+
+impl<T: RfbTransport> Session<T> {
+    pub fn new(transport: T, security: Security, name: &'static str) -> Self {
+        let mut session = Self {
+            transport,
+            security,
+            state: State::Version,
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+            tls_pending: false,
+            format: PixelFormat::SERVER,
+            zrle: false,
+            desktop_size: false,
+            zlib: ZlibStream::new(),
+            dirty: Vec::new(),
+            update_requested: false,
+            resize_pending: false,
+            skip: 0,
+            name,
+            stats: SessionStats::default(),
+        };
+        session.outbound.extend_from_slice(PROTOCOL_VERSION);
+        session
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state == State::Closed
+    }
+
+    pub fn supports_desktop_size(&self) -> bool {
+        self.desktop_size
+    }
+
+    /// Mark an area of the framebuffer for the next update
+    pub fn damage(&mut self, area: Area) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        let mut merged = area;
+        loop {
+            let before = self.dirty.len();
+            self.dirty.retain(|existing| {
+                if existing.touches(&merged) {
+                    merged = merged.union(existing);
+                    false
+                } else {
+                    true
+                }
+            });
+            if self.dirty.len() == before {
+                break;
+            }
+        }
+        self.dirty.push(merged);
+        if self.dirty.len() > MAX_DIRTY_RECTS {
+            let bounds = self.dirty.iter().skip(1).fold(self.dirty[0], |bounds, area| bounds.union(area));
+            self.dirty.clear();
+            self.dirty.push(bounds);
+        }
+    }
+
+    /// The framebuffer changed size; only valid for viewers supporting
+    /// DesktopSize, others have to be closed
+    pub fn resize(&mut self) {
+        self.resize_pending = true;
+        self.dirty.clear();
+    }
+
+    pub fn close(&mut self) {
+        self.state = State::Closed;
+        self.transport.close();
+    }
+
+    /// Read, handle and answer what the viewer sent; viewer input is
+    /// appended to `input`
+    pub fn poll(&mut self, framebuffer: &Framebuffer, input: &mut Vec<Input>) {
+        if self.state == State::Closed {
+            return;
+        }
+        self.receive();
+        while self.state != State::Closed && self.state != State::Closing {
+            match self.handle(framebuffer, input) {
+                Some(0) | None => break,
+                Some(consumed) => {
+                    self.inbound.drain(..consumed);
+                }
+            }
+        }
+        if self.state == State::Normal && self.update_requested && self.outbound.len() < OUTPUT_HIGH_WATER {
+            self.send_update(framebuffer);
+        }
+        self.flush();
+    }
+
+    fn receive(&mut self) {
+        let mut buffer = [0u8; READ_SIZE];
+        while self.state != State::Closed && !self.tls_pending {
+            match self.transport.read(&mut buffer) {
+                Ok(0) | Err(IoError::Closed) => {
+                    self.close();
+                    return;
+                }
+                Ok(read) => {
+                    // Cut text is discarded as it arrives rather than buffered
+                    let discarded = read.min(self.skip);
+                    self.skip -= discarded;
+                    self.inbound.extend_from_slice(&buffer[discarded..read]);
+                    if self.inbound.len() > MAX_INBOUND {
+                        self.close();
+                        return;
+                    }
+                }
+                Err(IoError::WouldBlock) => return,
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.outbound.is_empty() && self.state != State::Closed {
+            match self.transport.write(&self.outbound) {
+                Ok(written) => {
+                    self.outbound.drain(..written);
+                    self.stats.bytes_sent += written as u64;
+                }
+                Err(IoError::WouldBlock) => return,
+                Err(IoError::Closed) => {
+                    self.close();
+                    return;
+                }
+            }
+        }
+        if self.state == State::Closing {
+            self.close();
+        } else if self.tls_pending && self.outbound.is_empty() {
+            self.tls_pending = false;
+            let (certificate_handle, key_handle) = self.security.tls.unwrap_or_default();
+            if self.transport.start_tls(certificate_handle, key_handle).is_err() {
+                self.close();
+            } else if self.state == State::TlsSecured {
+                self.security_result(true);
+                self.flush();
+            }
+        }
+    }
+
+    /// Send a failure reason and close once it is out
+    fn fail(&mut self, reason: &[u8]) {
+        self.outbound.extend_from_slice(&(reason.len() as u32).to_be_bytes());
+        self.outbound.extend_from_slice(reason);
+        self.state = State::Closing;
+    }
+
+    fn security_result(&mut self, success: bool) {
+        if success {
+            self.outbound.extend_from_slice(&0u32.to_be_bytes());
+            self.state = State::ClientInit;
+        } else {
+            self.outbound.extend_from_slice(&1u32.to_be_bytes());
+            self.fail(b"authentication failed");
+        }
+    }
+
+    /// Handle the message at the head of `inbound`; bytes consumed, 0 or
+    /// None when it is incomplete
+    fn handle(&mut self, framebuffer: &Framebuffer, input: &mut Vec<Input>) -> Option<usize> {
+        let data = &self.inbound;
+        match self.state {
+            State::Version => {
+                let version = data.get(..PROTOCOL_VERSION.len())?;
+                let types = self.security.types();
+                if version != PROTOCOL_VERSION {
+                    self.outbound.push(0);
+                    self.fail(b"unsupported protocol version");
+                } else if types.is_empty() {
+                    self.outbound.push(0);
+                    self.fail(b"no security type configured");
+                } else {
+                    self.outbound.push(types.len() as u8);
+                    self.outbound.extend_from_slice(&types);
+                    self.state = State::SecurityType;
+                }
+                Some(PROTOCOL_VERSION.len())
+            }
+            State::SecurityType => {
+                let chosen = *data.first()?;
+                if !self.security.types().contains(&chosen) {
+                    self.security_result(false);
+                } else if chosen == SECURITY_NONE {
+                    self.security_result(true);
+                } else {
+                    // VeNCrypt 0.2
+                    self.outbound.extend_from_slice(&[0, 2]);
+                    self.state = State::VencryptVersion;
+                }
+                Some(1)
+            }
+            State::VencryptVersion => {
+                let version = data.get(..2)?;
+                if version != [0, 2] {
+                    self.outbound.push(1);
+                    self.state = State::Closing;
+                } else {
+                    let subtypes = self.security.vencrypt_subtypes();
+                    self.outbound.push(0);
+                    self.outbound.push(subtypes.len() as u8);
+                    for subtype in subtypes {
+                        self.outbound.extend_from_slice(&subtype.to_be_bytes());
+                    }
+                    self.state = State::VencryptSubtype;
+                }
+                Some(2)
+            }
+            State::VencryptSubtype => {
+                let chosen = u32::from_be_bytes(data.get(..4)?.try_into().ok()?);
+                if !self.security.vencrypt_subtypes().contains(&chosen) {
+                    self.outbound.push(0);
+                    self.state = State::Closing;
+                } else {
+                    self.outbound.push(1);
+                    self.tls_pending = true;
+                    if chosen == VENCRYPT_X509_PLAIN {
+                        self.state = State::PlainAuth;
+                    } else {
+                        self.state = State::TlsSecured;
+                    }
+                }
+                Some(4)
+            }
+            State::PlainAuth => {
+                let header = data.get(..8)?;
+                let user_length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+                let password_length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+                if user_length > MAX_CREDENTIAL || password_length > MAX_CREDENTIAL {
+                    self.security_result(false);
+                    return Some(0);
+                }
+                let password = data.get(8 + user_length..8 + user_length + password_length)?;
+                let accepted = self.security.password.is_some_and(|stored| stored.verify(password));
+                self.security_result(accepted);
+                Some(8 + user_length + password_length)
+            }
+            State::ClientInit => {
+                // The shared flag is ignored: every viewer shares the output
+                data.first()?;
+                self.outbound.extend_from_slice(&framebuffer.width.to_be_bytes());
+                self.outbound.extend_from_slice(&framebuffer.height.to_be_bytes());
+                self.outbound.extend_from_slice(&PixelFormat::SERVER.encode());
+                self.outbound.extend_from_slice(&(self.name.len() as u32).to_be_bytes());
+                self.outbound.extend_from_slice(self.name.as_bytes());
+                self.format = PixelFormat::SERVER;
+                self.state = State::Normal;
+                Some(1)
+            }
+            State::Normal => self.handle_message(framebuffer, input),
+            State::TlsSecured | State::Closing | State::Closed => None,
+        }
+    }
+
+    fn handle_message(&mut self, framebuffer: &Framebuffer, input: &mut Vec<Input>) -> Option<usize> {
+        let data = &self.inbound;
+        let u16_at = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        match *data.first()? {
+            MSG_SET_PIXEL_FORMAT => {
+                let message = data.get(..4 + PIXEL_FORMAT_SIZE)?;
+                match PixelFormat::parse(&message[4..]) {
+                    Some(format) => self.format = format,
+                    None => self.close(),
+                }
+                Some(4 + PIXEL_FORMAT_SIZE)
+            }
+            MSG_SET_ENCODINGS => {
+                let count = data.get(..4).map(|_| u16_at(2) as usize)?;
+                let encodings = data.get(4..4 + 4 * count)?;
+                self.zrle = false;
+                self.desktop_size = false;
+                for encoding in encodings.chunks_exact(4) {
+                    match i32::from_be_bytes([encoding[0], encoding[1], encoding[2], encoding[3]]) {
+                        ENCODING_ZRLE => self.zrle = true,
+                        ENCODING_DESKTOP_SIZE => self.desktop_size = true,
+                        _ => {}
+                    }
+                }
+                Some(4 + 4 * count)
+            }
+            MSG_UPDATE_REQUEST => {
+                data.get(..10)?;
+                let incremental = data[1] != 0;
+                let area = Area::new(u16_at(2), u16_at(4), u16_at(6), u16_at(8));
+                if !incremental {
+                    if let Some(area) = area.clip(framebuffer.width, framebuffer.height) {
+                        self.damage(area);
+                    }
+                }
+                self.update_requested = true;
+                Some(10)
+            }
+            MSG_KEY_EVENT => {
+                let message = data.get(..8)?;
+                let keysym = u32::from_be_bytes([message[4], message[5], message[6], message[7]]);
+                input.push(Input::Key { keysym, down: message[1] != 0 });
+                Some(8)
+            }
+            MSG_POINTER_EVENT => {
+                data.get(..6)?;
+                input.push(Input::Pointer { x: u16_at(2), y: u16_at(4), buttons: data[1] });
+                Some(6)
+            }
+            MSG_CLIENT_CUT_TEXT => {
+                let header = data.get(..8)?;
+                let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+                let buffered = (data.len() - 8).min(length);
+                self.skip = length - buffered;
+                Some(8 + buffered)
+            }
+            _ => {
+                self.close();
+                None
+            }
+        }
+    }
+
+    fn send_update(&mut self, framebuffer: &Framebuffer) {
+        let mut rects = Vec::new();
+        if self.resize_pending {
+            self.resize_pending = false;
+            rects.push((Area::new(0, 0, framebuffer.width, framebuffer.height), ENCODING_DESKTOP_SIZE));
+            self.dirty.clear();
+            self.damage(Area::new(0, 0, framebuffer.width, framebuffer.height));
+        }
+        let encoding = if self.zrle { ENCODING_ZRLE } else { ENCODING_RAW };
+        for area in self.dirty.drain(..) {
+            if let Some(area) = area.clip(framebuffer.width, framebuffer.height) {
+                rects.push((area, encoding));
+            }
+        }
+        if rects.is_empty() {
+            return;
+        }
+
+        self.outbound.push(MSG_FRAMEBUFFER_UPDATE);
+        self.outbound.push(0);
+        self.outbound.extend_from_slice(&(rects.len() as u16).to_be_bytes());
+        for (area, encoding) in rects {
+            self.outbound.extend_from_slice(&area.x.to_be_bytes());
+            self.outbound.extend_from_slice(&area.y.to_be_bytes());
+            self.outbound.extend_from_slice(&area.width.to_be_bytes());
+            self.outbound.extend_from_slice(&area.height.to_be_bytes());
+            self.outbound.extend_from_slice(&encoding.to_be_bytes());
+            let stride = framebuffer.width as usize;
+            let (x, y) = (area.x as usize, area.y as usize);
+            let (width, height) = (area.width as usize, area.height as usize);
+            match encoding {
+                ENCODING_ZRLE => zrle::encode_rect(
+                    &self.format,
+                    &framebuffer.pixels,
+                    stride,
+                    (x, y, width, height),
+                    &mut self.zlib,
+                    &mut self.outbound,
+                ),
+                ENCODING_RAW => {
+                    for row in y..y + height {
+                        for &pixel in &framebuffer.pixels[row * stride + x..row * stride + x + width] {
+                            self.format.put(self.format.convert(pixel), &mut self.outbound);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.update_requested = false;
+        self.stats.updates += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[derive(Default)]
+    struct MockTransport {
+        input: Vec<u8>,
+        output: Vec<u8>,
+        tls: Option<(u64, u64)>,
+        /// Output length when TLS was started
+        tls_at: usize,
+        closed: bool,
+    }
+
+    impl Transport for MockTransport {
+        fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+            if self.input.is_empty() {
+                return Err(IoError::WouldBlock);
+            }
+            let length = buffer.len().min(self.input.len());
+            buffer[..length].copy_from_slice(&self.input[..length]);
+            self.input.drain(..length);
+            Ok(length)
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<usize, IoError> {
+            self.output.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn close(&mut self) {
+            self.closed = true;
+        }
+    }
+
+    impl RfbTransport for MockTransport {
+        fn start_tls(&mut self, certificate_handle: u64, key_handle: u64) -> Result<(), i32> {
+            self.tls = Some((certificate_handle, key_handle));
+            self.tls_at = self.output.len();
+            Ok(())
+        }
+    }
+
+    fn exchange(session: &mut Session<MockTransport>, framebuffer: &Framebuffer, sent: &[u8]) -> Vec<u8> {
+        session.transport.input.extend_from_slice(sent);
+        session.poll(framebuffer, &mut Vec::new());
+        core::mem::take(&mut session.transport.output)
+    }
+
+    #[test]
+    fn test_negotiates_none_and_sends_raw_updates() {
+        let security = Security { allow_none: true, ..Security::default() };
+        let framebuffer = Framebuffer { width: 2, height: 1, pixels: vec![0x12_3456, 0xab_cdef] };
+        let mut session = Session::new(MockTransport::default(), security, "orion");
+
+        let mut expected = PROTOCOL_VERSION.to_vec();
+        expected.extend_from_slice(&[1, SECURITY_NONE]);
+        assert_eq!(exchange(&mut session, &framebuffer, PROTOCOL_VERSION), expected);
+        assert_eq!(exchange(&mut session, &framebuffer, &[SECURITY_NONE]), [0, 0, 0, 0]);
+
+        let server_init = exchange(&mut session, &framebuffer, &[1]);
+        assert_eq!(server_init[..4], [0, 2, 0, 1]);
+        assert_eq!(server_init[4..20], PixelFormat::SERVER.encode());
+        assert_eq!(server_init[20..], [0, 0, 0, 5, b'o', b'r', b'i', b'o', b'n']);
+
+        // Pointer event split across two reads, then a full update request
+        let mut input = Vec::new();
+        session.transport.input.extend_from_slice(&[MSG_POINTER_EVENT, 1, 0]);
+        session.poll(&framebuffer, &mut input);
+        session.transport.input.extend_from_slice(&[1, 0, 0]);
+        session.transport.input.extend_from_slice(&[MSG_UPDATE_REQUEST, 0, 0, 0, 0, 0, 0, 2, 0, 1]);
+        session.poll(&framebuffer, &mut input);
+        assert_eq!(input, [Input::Pointer { x: 1, y: 0, buttons: 1 }]);
+        assert_eq!(
+            session.transport.output,
+            [0, 0, 0, 1, 0, 0, 0, 0, 0, 2, 0, 1, 0, 0, 0, 0, 0x56, 0x34, 0x12, 0, 0xef, 0xcd, 0xab, 0]
+        );
+
+        // Nothing is sent without a request or without damage
+        session.transport.output.clear();
+        session.damage(Area::new(0, 0, 1, 1));
+        assert!(exchange(&mut session, &framebuffer, &[]).is_empty());
+        let update = exchange(&mut session, &framebuffer, &[MSG_UPDATE_REQUEST, 1, 0, 0, 0, 0, 0, 2, 0, 1]);
+        assert_eq!(update[4..12], [0, 0, 0, 0, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_upgrades_to_tls_before_plain_auth() {
+        let salt = [7u8; PASSWORD_SALT_SIZE];
+        let security =
+            Security { allow_none: false, tls: Some((3, 4)), password: Some(Password::new(salt, b"secret")) };
+        let framebuffer = Framebuffer::new(4, 4);
+        let mut session = Session::new(MockTransport::default(), security, "orion");
+
+        let offered = exchange(&mut session, &framebuffer, PROTOCOL_VERSION);
+        assert_eq!(offered[12..], [1, SECURITY_VENCRYPT]);
+        assert_eq!(exchange(&mut session, &framebuffer, &[SECURITY_VENCRYPT]), [0, 2]);
+        assert_eq!(exchange(&mut session, &framebuffer, &[0, 2]), [0, 1, 0, 0, 1, 6]);
+
+        // TLS starts right after the acknowledgement
+        assert_eq!(exchange(&mut session, &framebuffer, &VENCRYPT_X509_PLAIN.to_be_bytes()), [1]);
+        assert_eq!(session.transport.tls, Some((3, 4)));
+        assert_eq!(session.transport.tls_at, 1);
+
+        let mut credentials = vec![0, 0, 0, 4, 0, 0, 0, 5];
+        credentials.extend_from_slice(b"userwrong");
+        let result = exchange(&mut session, &framebuffer, &credentials);
+        assert_eq!(result[..4], [0, 0, 0, 1]);
+        assert!(session.is_closed() && session.transport.closed);
+
+        let mut session = Session::new(MockTransport::default(), security, "orion");
+        exchange(&mut session, &framebuffer, PROTOCOL_VERSION);
+        exchange(&mut session, &framebuffer, &[SECURITY_VENCRYPT, 0, 2]);
+        exchange(&mut session, &framebuffer, &VENCRYPT_X509_PLAIN.to_be_bytes());
+        let mut credentials = vec![0, 0, 0, 0, 0, 0, 0, 6];
+        credentials.extend_from_slice(b"secret");
+        assert_eq!(exchange(&mut session, &framebuffer, &credentials), [0, 0, 0, 0]);
+
+        // Security None was not offered
+        let mut session = Session::new(MockTransport::default(), security, "orion");
+        exchange(&mut session, &framebuffer, PROTOCOL_VERSION);
+        exchange(&mut session, &framebuffer, &[SECURITY_NONE]);
+        assert!(session.is_closed());
+    }
+
+    #[test]
+    fn test_announces_desktop_size() {
+        let security = Security { allow_none: true, ..Security::default() };
+        let mut framebuffer = Framebuffer::new(2, 2);
+        let mut session = Session::new(MockTransport::default(), security, "");
+        exchange(&mut session, &framebuffer, PROTOCOL_VERSION);
+        exchange(&mut session, &framebuffer, &[SECURITY_NONE, 1]);
+
+        let mut set_encodings = vec![MSG_SET_ENCODINGS, 0, 0, 2];
+        set_encodings.extend_from_slice(&ENCODING_ZRLE.to_be_bytes());
+        set_encodings.extend_from_slice(&ENCODING_DESKTOP_SIZE.to_be_bytes());
+        exchange(&mut session, &framebuffer, &set_encodings);
+        assert!(session.supports_desktop_size());
+
+        framebuffer = Framebuffer::new(3, 1);
+        session.resize();
+        let update = exchange(&mut session, &framebuffer, &[MSG_UPDATE_REQUEST, 1, 0, 0, 0, 0, 0, 3, 0, 1]);
+        assert_eq!(update[..4], [0, 0, 0, 2]);
+        assert_eq!(update[4..16], [0, 0, 0, 0, 0, 3, 0, 1, 0xff, 0xff, 0xff, 0x21]);
+        assert_eq!(update[16..28], [0, 0, 0, 0, 0, 3, 0, 1, 0, 0, 0, 16]);
+    }
+}
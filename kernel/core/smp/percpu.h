@@ -0,0 +1,68 @@
+/*
+ * Orion Operating System - Per-CPU Data
+ *
+ * Variables defined with DEFINE_PER_CPU are placed in the .percpu section
+ * of the kernel image. That section is a template: smp_init gives every
+ * CPU other than the boot CPU its own copy, and per_cpu_ptr adds the CPU's
+ * offset to the variable's address. The boot CPU keeps the template
+ * itself, so per-CPU variables work from the very first instruction.
+ *
+ * A CPU only ever writes its own copy, which makes per-CPU counters the
+ * natural home for hot statistics: this_cpu_add takes no lock and never
+ * bounces a cache line between CPUs. Readers fold every CPU's copy with
+ * per_cpu_sum and see a value that is exact once writers are quiet.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_PERCPU_H
+#define ORION_PERCPU_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Offset of each CPU's copy from the template, 0 for the boot CPU
+extern uintptr_t percpu_offset[MAX_CPUS];
+
+// Bounds of the template, from the linker script
+extern char __percpu_start[];
+extern char __percpu_end[];
+
+#define DEFINE_PER_CPU(type, name) __attribute__((section(".percpu"))) __typeof__(type) name
+#define DECLARE_PER_CPU(type, name) extern __typeof__(type) name
+
+#define per_cpu_ptr(var, cpu) ((__typeof__(&(var)))((uintptr_t) & (var) + percpu_offset[(cpu)]))
+#define per_cpu(var, cpu) (*per_cpu_ptr(var, cpu))
+#define this_cpu_ptr(var) per_cpu_ptr(var, arch_get_current_cpu())
+
+// Single-copy atomic so that an interrupt on the same CPU cannot lose an
+// update; there is never contention, so this costs no more than a store
+#define this_cpu_add(var, n) __atomic_fetch_add(this_cpu_ptr(var), (n), __ATOMIC_RELAXED)
+#define this_cpu_inc(var) this_cpu_add(var, 1)
+#define this_cpu_read(var) __atomic_load_n(this_cpu_ptr(var), __ATOMIC_RELAXED)
+#define this_cpu_write(var, v) __atomic_store_n(this_cpu_ptr(var), (v), __ATOMIC_RELAXED)
+
+    // Sum of a 64-bit counter over every possible CPU
+    uint64_t percpu_sum_u64(const uint64_t *var);
+
+    // Fold a per-CPU structure made only of 64-bit counters into `out`
+    void percpu_sum_struct_u64(const void *var, void *out, size_t size);
+
+    // Zero a per-CPU variable on every CPU
+    void percpu_clear(void *var, size_t size);
+
+#define per_cpu_sum(var) percpu_sum_u64(&(var))
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_PERCPU_H
@@ -0,0 +1,211 @@
+/*
+ * Orion Operating System - Symmetric Multiprocessing
+ *
+ * Per-CPU areas, the online mask, IPI message passing and TLB shootdown.
+ * Message bits are set with an atomic OR before the target is interrupted
+ * and taken with an atomic exchange by the target, so a message posted
+ * while the target is already serving its IPI is never lost: at worst it
+ * is served by the interrupt that was raised for it.
+ *
+ * A single shootdown runs at a time. The initiator publishes the range,
+ * sets the mask of CPUs that still have to flush, interrupts them and
+ * waits for the mask to drain, serving its own IPIs meanwhile in case the
+ * CPUs it waits for are themselves waiting on it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/smp.h>
+#include <orion/percpu.h>
+#include <orion/scheduler.h>
+
+#define PERCPU_ALIGN 64
+
+uintptr_t percpu_offset[MAX_CPUS];
+
+static atomic64_t g_online_mask = ATOMIC_VAR_INIT(1); // Boot CPU
+static atomic64_t g_ipi_pending[MAX_CPUS];
+static uint32_t g_possible_cpus = 1;
+
+static spinlock_t g_shootdown_lock = SPINLOCK_INIT;
+static volatile uint64_t g_shootdown_vaddr;
+static volatile uint64_t g_shootdown_pages;
+static atomic64_t g_shootdown_waiting; // CPUs that have not flushed yet
+
+// ========================================
+// PER-CPU AREAS
+// ========================================
+
+void smp_init(void)
+{
+    size_t size = (size_t)(__percpu_end - __percpu_start);
+    uint32_t count = arch_get_cpu_count();
+    if (count > MAX_CPUS)
+    {
+        kwarn("SMP: %u CPUs present, only %u supported", count, MAX_CPUS);
+        count = MAX_CPUS;
+    }
+    g_possible_cpus = count ? count : 1;
+
+    for (uint32_t cpu = 1; cpu < g_possible_cpus && size; cpu++)
+    {
+        uint8_t *area = kmalloc(size + PERCPU_ALIGN);
+        if (!area)
+        {
+            kernel_panic("SMP: out of memory for per-CPU areas");
+        }
+        area = (uint8_t *)(((uintptr_t)area + PERCPU_ALIGN - 1) & ~(uintptr_t)(PERCPU_ALIGN - 1));
+        memcpy(area, __percpu_start, size);
+        percpu_offset[cpu] = (uintptr_t)area - (uintptr_t)__percpu_start;
+    }
+
+    kinfo("SMP: %u possible CPUs, %llu bytes of per-CPU data each", g_possible_cpus, (unsigned long long)size);
+}
+
+uint64_t percpu_sum_u64(const uint64_t *var)
+{
+    uint64_t sum = 0;
+    for (uint32_t cpu = 0; cpu < g_possible_cpus; cpu++)
+    {
+        sum += __atomic_load_n((const uint64_t *)((uintptr_t)var + percpu_offset[cpu]), __ATOMIC_RELAXED);
+    }
+    return sum;
+}
+
+void percpu_sum_struct_u64(const void *var, void *out, size_t size)
+{
+    uint64_t *totals = out;
+    size_t fields = size / sizeof(uint64_t);
+    memset(out, 0, size);
+    for (uint32_t cpu = 0; cpu < g_possible_cpus; cpu++)
+    {
+        const uint64_t *copy = (const uint64_t *)((uintptr_t)var + percpu_offset[cpu]);
+        for (size_t i = 0; i < fields; i++)
+        {
+            totals[i] += __atomic_load_n(&copy[i], __ATOMIC_RELAXED);
+        }
+    }
+}
+
+void percpu_clear(void *var, size_t size)
+{
+    for (uint32_t cpu = 0; cpu < g_possible_cpus; cpu++)
+    {
+        memset((void *)((uintptr_t)var + percpu_offset[cpu]), 0, size);
+    }
+}
+
+// ========================================
+// CPU BRING-UP
+// ========================================
+
+void smp_boot(void)
+{
+    if (g_possible_cpus == 1)
+    {
+        kinfo("SMP: single CPU system");
+        return;
+    }
+    arch_smp_boot();
+    kinfo("SMP: %u of %u CPUs online", smp_online_count(), g_possible_cpus);
+}
+
+void smp_secondary_main(uint32_t cpu)
+{
+    atomic_fetch_or(&g_online_mask, 1ULL << cpu);
+    kinfo("SMP: CPU %u entering the scheduler", cpu);
+    arch_enable_interrupts();
+    scheduler_idle_loop();
+}
+
+bool smp_cpu_is_online(uint32_t cpu)
+{
+    return cpu < MAX_CPUS && (atomic_load(&g_online_mask) & (1ULL << cpu));
+}
+
+uint32_t smp_online_count(void)
+{
+    return (uint32_t)__builtin_popcountll(atomic_load(&g_online_mask));
+}
+
+uint64_t smp_online_mask(void)
+{
+    return atomic_load(&g_online_mask);
+}
+
+// ========================================
+// INTER-PROCESSOR INTERRUPTS
+// ========================================
+
+int smp_send_ipi(uint32_t cpu, uint32_t message)
+{
+    if (!smp_cpu_is_online(cpu))
+    {
+        return -OR_EINVAL;
+    }
+    atomic_fetch_or(&g_ipi_pending[cpu], message);
+    if (cpu == arch_get_current_cpu())
+    {
+        return OR_OK;
+    }
+    return arch_smp_send_ipi(cpu);
+}
+
+void smp_handle_ipi(void)
+{
+    uint32_t cpu = arch_get_current_cpu();
+    uint64_t pending = __atomic_exchange_n(&g_ipi_pending[cpu].value, 0, __ATOMIC_ACQ_REL);
+
+    if (pending & SMP_IPI_TLB_SHOOTDOWN)
+    {
+        arch_tlb_flush_local(g_shootdown_vaddr, g_shootdown_pages);
+        atomic_fetch_and(&g_shootdown_waiting, ~(1ULL << cpu));
+    }
+    if (pending & SMP_IPI_RESCHEDULE)
+    {
+        scheduler_ipi_reschedule();
+    }
+}
+
+void smp_tlb_shootdown(uint64_t vaddr, uint64_t pages)
+{
+    arch_tlb_flush_local(vaddr, pages);
+
+    uint32_t self = arch_get_current_cpu();
+    uint64_t others = smp_online_mask() & ~(1ULL << self);
+    if (!others)
+    {
+        return;
+    }
+
+    while (!spin_trylock(&g_shootdown_lock))
+    {
+        smp_handle_ipi();
+        arch_pause();
+    }
+
+    g_shootdown_vaddr = vaddr;
+    g_shootdown_pages = pages;
+    atomic_store(&g_shootdown_waiting, others);
+    for (uint32_t cpu = 0; cpu < MAX_CPUS; cpu++)
+    {
+        if (others & (1ULL << cpu))
+        {
+            smp_send_ipi(cpu, SMP_IPI_TLB_SHOOTDOWN);
+        }
+    }
+    while (atomic_load(&g_shootdown_waiting))
+    {
+        smp_handle_ipi();
+        arch_pause();
+    }
+
+    spin_unlock(&g_shootdown_lock);
+}
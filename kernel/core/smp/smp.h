@@ -0,0 +1,75 @@
+/*
+ * Orion Operating System - Symmetric Multiprocessing
+ *
+ * Architecture-independent side of SMP. The architecture discovers its
+ * CPUs (ACPI MADT on x86_64, the device tree on AArch64 and RISC-V) and
+ * starts them (INIT-SIPI, PSCI CPU_ON, SBI HSM hart_start) when the kernel
+ * calls smp_boot; each one then enters smp_secondary_main, joins the
+ * online mask and runs the scheduler's idle loop.
+ *
+ * Cross-CPU requests are IPIs carrying a set of pending message bits. The
+ * architecture delivers a single interrupt per CPU and calls
+ * smp_handle_ipi from its vector, which serves every message pending for
+ * that CPU: rescheduling and TLB shootdown.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_SMP_H
+#define ORION_SMP_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// IPI messages, as bits of the per-CPU pending mask
+#define SMP_IPI_RESCHEDULE (1U << 0)
+#define SMP_IPI_TLB_SHOOTDOWN (1U << 1)
+
+// smp_tlb_shootdown page count meaning the whole address space
+#define SMP_TLB_FLUSH_ALL 0
+
+    // Boot CPU, once the heap is up: set up per-CPU areas
+    void smp_init(void);
+
+    // Boot CPU, once the scheduler is up: start the other CPUs
+    void smp_boot(void);
+
+    // Entry of every secondary CPU once its architecture state is ready.
+    // Marks it online and runs the idle loop; never returns
+    void smp_secondary_main(uint32_t cpu);
+
+    bool smp_cpu_is_online(uint32_t cpu);
+    uint32_t smp_online_count(void);
+    uint64_t smp_online_mask(void);
+
+    // Post `message` to `cpu` and interrupt it. Sending to the current CPU
+    // only records the message
+    int smp_send_ipi(uint32_t cpu, uint32_t message);
+
+    // Called by the architecture's IPI vector with interrupts masked, and
+    // by CPUs spinning on a cross-CPU request so that two CPUs waiting on
+    // each other both make progress
+    void smp_handle_ipi(void);
+
+    // Invalidate `pages` pages from `vaddr` (or everything with
+    // SMP_TLB_FLUSH_ALL) on every online CPU, returning once all are done
+    void smp_tlb_shootdown(uint64_t vaddr, uint64_t pages);
+
+    // Provided by the architecture
+    int arch_smp_boot(void);
+    int arch_smp_send_ipi(uint32_t cpu);
+    void arch_tlb_flush_local(uint64_t vaddr, uint64_t pages);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_SMP_H
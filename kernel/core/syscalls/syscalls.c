@@ -1,13 +1,67 @@
 // Orion system call handler
 #include <orion/kernel.h>
 #include <orion/types.h>
+#include <orion/mm.h>
 #include <orion/syscalls.h>
+#include <orion/measured_boot.h>
+#include <orion/sandbox.h>
+#include <orion/wallclock.h>
+#include <orion/cpufreq.h>
+#include <orion/aslr.h>
+#include <orion/vma.h>
+#include <orion/oom.h>
+#include <orion/ksm.h>
+#include <orion/namespace.h>
+#include <orion/mac.h>
+#include <orion/hypervisor.h>
+#include <orion/scheduler.h>
+#include <orion/sched_rt.h>
+#include <orion/bootinfo.h>
+#include <orion/earlycon.h>
+#include <orion/ipc_stats.h>
 
 // Missing function declarations (stubs)
 extern void thread_exit(int exit_code);
 extern or_cap_t ipc_port_create(uint64_t pid);
 extern int ipc_send_message(or_cap_t port, void* data, uint64_t size, uint64_t timeout_ns);
 extern int ipc_recv_message(or_cap_t port, void* buffer, uint64_t size, uint64_t timeout_ns);
+extern int ipc_call(or_cap_t port, const void* request, uint64_t request_size, void* reply, uint64_t reply_size,
+                    uint64_t timeout_ns);
+extern int ipc_reply_recv(or_cap_t port, const void* reply, uint64_t reply_size, void* request, uint64_t request_size);
+extern uint64_t security_get_random(void);
+extern int security_audit_user_event(uint32_t event_type, const char* description);
+extern bool security_check_syscall_allowed(uint64_t syscall_num, uint64_t pid);
+extern bool security_check_memory_limit(uint64_t pid, uint64_t requested_bytes);
+
+int64_t sys_measure_log_impl(uint32_t index, measure_event_t* event);
+int64_t sys_boot_framebuffer_impl(boot_framebuffer_t* out);
+int64_t sys_earlycon_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2, uint64_t arg3);
+int64_t sys_proc_fork_impl(uint64_t entry_point, uint64_t stack_pointer, uint64_t arg);
+int64_t sys_proc_info_impl(uint64_t after_pid, proc_info_t* info);
+int64_t sys_sandbox_load_impl(uint64_t pid, const sandbox_profile_t* profile);
+int64_t sys_oom_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2, uint64_t arg3);
+int64_t sys_ksm_ctl_impl(uint32_t op, uint64_t arg);
+int64_t sys_ns_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2);
+int64_t sys_mac_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2);
+int64_t sys_hv_ctl_impl(uint32_t op, uint32_t vm, uint32_t vcpu, uint64_t arg);
+int64_t sys_clock_adjust_impl(uint32_t op, uint64_t arg);
+int64_t sys_cpufreq_impl(uint32_t op, uint32_t cpu, const char* name, char* buf, uint64_t size);
+int64_t sys_sched_attr_impl(uint32_t op, uint64_t tid, sched_rt_attr_t* attr);
+int64_t sys_port_call_impl(or_cap_t port, const void* request, uint64_t request_size, void* reply,
+                           uint64_t reply_size, uint64_t timeout_ns);
+int64_t sys_port_reply_recv_impl(or_cap_t port, const void* reply, uint64_t reply_size, void* request,
+                                 uint64_t request_size);
+int64_t sys_ipc_stats_impl(uint64_t cursor, ipc_port_stats_t* stats);
+
+// Largest request served by SYS_RANDOM in one call (short read beyond)
+#define SYS_RANDOM_MAX_BYTES 256
+
+// Audit descriptions are stored in 128-byte slots (NUL included)
+#define AUDIT_EMIT_MAX_BYTES 128
+
+// Longest cpufreq attribute name and value (NUL included)
+#define CPUFREQ_ATTR_NAME_MAX 32
+#define CPUFREQ_ATTR_VALUE_MAX 64
 
 // System call table
 typedef int64_t (*syscall_handler_t)(uint64_t arg1, uint64_t arg2, 
@@ -19,11 +73,16 @@ static syscall_handler_t syscall_table[MAX_SYSCALLS] = {
     [SYS_EXIT]          = (syscall_handler_t)sys_exit_impl,
     [SYS_YIELD]         = (syscall_handler_t)sys_yield_impl,
     [SYS_PROC_CREATE]   = (syscall_handler_t)sys_proc_create_impl,
+    [SYS_PROC_FORK]     = (syscall_handler_t)sys_proc_fork_impl,
+    [SYS_PROC_INFO]     = (syscall_handler_t)sys_proc_info_impl,
     [SYS_THREAD_CREATE] = (syscall_handler_t)sys_thread_create_impl,
     [SYS_WAIT]          = (syscall_handler_t)sys_wait_impl,
     [SYS_SIGNAL]        = (syscall_handler_t)sys_signal_impl,
     [SYS_GETPID]        = (syscall_handler_t)sys_getpid_impl,
     [SYS_GETTID]        = (syscall_handler_t)sys_gettid_impl,
+    [SYS_SCHED_ATTR]    = (syscall_handler_t)sys_sched_attr_impl,
+    [SYS_NS_CTL]        = (syscall_handler_t)sys_ns_ctl_impl,
+    [SYS_HV_CTL]        = (syscall_handler_t)sys_hv_ctl_impl,
     
     // Memory
     [SYS_VM_MAP]        = (syscall_handler_t)sys_vm_map_impl,
@@ -33,6 +92,8 @@ static syscall_handler_t syscall_table[MAX_SYSCALLS] = {
     [SYS_SHM_ATTACH]    = (syscall_handler_t)sys_shm_attach_impl,
     [SYS_SHM_DETACH]    = (syscall_handler_t)sys_shm_detach_impl,
     [SYS_MADVISE]       = (syscall_handler_t)sys_madvise_impl,
+    [SYS_OOM_CTL]       = (syscall_handler_t)sys_oom_ctl_impl,
+    [SYS_KSM_CTL]       = (syscall_handler_t)sys_ksm_ctl_impl,
     
     // IPC
     [SYS_PORT_CREATE]   = (syscall_handler_t)sys_port_create_impl,
@@ -40,6 +101,9 @@ static syscall_handler_t syscall_table[MAX_SYSCALLS] = {
     [SYS_PORT_RECV]     = (syscall_handler_t)sys_port_recv_impl,
     [SYS_PORT_SHARE]    = (syscall_handler_t)sys_port_share_impl,
     [SYS_MSG_FORWARD]   = (syscall_handler_t)sys_msg_forward_impl,
+    [SYS_PORT_CALL]     = (syscall_handler_t)sys_port_call_impl,
+    [SYS_PORT_REPLY_RECV] = (syscall_handler_t)sys_port_reply_recv_impl,
+    [SYS_IPC_STATS]     = (syscall_handler_t)sys_ipc_stats_impl,
     
     // Time
     [SYS_CLOCK_GET]     = (syscall_handler_t)sys_clock_get_impl,
@@ -47,6 +111,10 @@ static syscall_handler_t syscall_table[MAX_SYSCALLS] = {
     [SYS_TIMER_START]   = (syscall_handler_t)sys_timer_start_impl,
     [SYS_TIMER_STOP]    = (syscall_handler_t)sys_timer_stop_impl,
     [SYS_NANOSLEEP]     = (syscall_handler_t)sys_nanosleep_impl,
+    [SYS_CLOCK_ADJUST]  = (syscall_handler_t)sys_clock_adjust_impl,
+    
+    // Power
+    [SYS_CPUFREQ]       = (syscall_handler_t)sys_cpufreq_impl,
     
     // I/O
     [SYS_IO_SUBMIT]     = (syscall_handler_t)sys_io_submit_impl,
@@ -64,9 +132,13 @@ static syscall_handler_t syscall_table[MAX_SYSCALLS] = {
     [SYS_CAP_QUERY]     = (syscall_handler_t)sys_cap_query_impl,
     [SYS_SANDBOX_LOAD]  = (syscall_handler_t)sys_sandbox_load_impl,
     [SYS_AUDIT_EMIT]    = (syscall_handler_t)sys_audit_emit_impl,
+    [SYS_MAC_CTL]       = (syscall_handler_t)sys_mac_ctl_impl,
+    [SYS_MEASURE_LOG]   = (syscall_handler_t)sys_measure_log_impl,
     
     // Miscellaneous
     [SYS_INFO]          = (syscall_handler_t)sys_info_impl,
+    [SYS_BOOT_FRAMEBUFFER] = (syscall_handler_t)sys_boot_framebuffer_impl,
+    [SYS_EARLYCON_CTL]  = (syscall_handler_t)sys_earlycon_ctl_impl,
     [SYS_DBG_TRACE]     = (syscall_handler_t)sys_dbg_trace_impl,
     [SYS_RANDOM]        = (syscall_handler_t)sys_random_impl
 };
@@ -90,6 +162,12 @@ int64_t syscall_handler(uint64_t syscall_num, uint64_t arg1, uint64_t arg2,
         return -OR_ENOSYS;
     }
     
+    // Enforce the caller's sandbox profile, if any
+    process_t* caller = scheduler_get_current_process();
+    if (caller && !security_check_syscall_allowed(syscall_num, caller->pid)) {
+        return -OR_EPERM;
+    }
+    
     // Call the handler
     return handler(arg1, arg2, arg3, arg4, arg5, arg6);
 }
@@ -209,6 +287,10 @@ int64_t sys_vm_map_impl(or_vm_map_t* map_params) {
         return -OR_EINVAL;
     }
     
+    if (!security_check_memory_limit(current_process->pid, map_params->length)) {
+        return -OR_ENOMEM;
+    }
+    
     // Convert protection flags
     uint64_t vm_flags = 0;
     if (map_params->prot & VM_PROT_READ) vm_flags |= VM_FLAG_READ;
@@ -220,42 +302,44 @@ int64_t sys_vm_map_impl(or_vm_map_t* map_params) {
     size_t pages_needed = map_params->length / PAGE_SIZE;
     
     uint64_t vaddr;
-    if (map_params->flags & VM_MAP_FIXED && map_params->addr) {
-        // Fixed address requested
+    if (map_params->flags & OR_MAP_DMA) {
+        // DMA buffers stay resident and overruns fault on a guard page
+        // instead of corrupting the neighbouring mapping. Large ones, such
+        // as framebuffers and NVMe queues, can ask for huge pages
+        vaddr = (map_params->flags & OR_MAP_HUGE)
+                    ? aslr_alloc_dma_huge(current_process->vm_space, current_process->pid, pages_needed, vm_flags)
+                    : aslr_alloc_dma(current_process->vm_space, current_process->pid, pages_needed, vm_flags);
+        if (!vaddr) {
+            return -OR_ENOMEM;
+        }
+        vmm_lock_range(current_process->vm_space, vaddr, pages_needed, true);
+    } else if (map_params->flags & VM_MAP_FIXED && map_params->addr) {
+        // Fixed address requested, populated on first touch
         vaddr = map_params->addr;
         
-        // Check if space is free
+        // Areas not touched yet have no pages, so check both
+        if (vma_overlaps(current_process->vm_space, vaddr, vaddr + map_params->length)) {
+            return -OR_EINVAL;
+        }
         for (size_t i = 0; i < pages_needed; i++) {
             if (mmu_is_valid_addr(vaddr + i * PAGE_SIZE)) {
                 return -OR_EINVAL; // Already mapped
             }
         }
         
-        // Map pages one by one
-        for (size_t i = 0; i < pages_needed; i++) {
-            uint64_t page_vaddr = vaddr + i * PAGE_SIZE;
-            uint64_t page_paddr = pmm_alloc_page();
-            
-            if (!page_paddr) {
-                // Clean up already mapped pages
-                for (size_t j = 0; j < i; j++) {
-                    vmm_unmap_page(current_process->vm_space, vaddr + j * PAGE_SIZE);
-                }
-                return -OR_ENOMEM;
-            }
-            
-            if (vmm_map_page(current_process->vm_space, page_vaddr, page_paddr, vm_flags) != OR_OK) {
-                pmm_free_page(page_paddr);
-                // Clean up already mapped pages
-                for (size_t j = 0; j < i; j++) {
-                    vmm_unmap_page(current_process->vm_space, vaddr + j * PAGE_SIZE);
-                }
-                return -OR_ENOMEM;
-            }
+        vma_t area = {0};
+        area.start = vaddr;
+        area.end = vaddr + map_params->length;
+        area.prot = vm_flags;
+        area.type = VMA_ANONYMOUS;
+        area.flags = VMA_LAZY;
+        if (vma_insert(current_process->vm_space, &area) != OR_OK) {
+            return -OR_EINVAL;
         }
     } else {
-        // Automatic address allocation
-        vaddr = vmm_alloc_pages(current_process->vm_space, pages_needed, vm_flags);
+        // Automatic address allocation, populated on first touch
+        vaddr = vma_map_anonymous(current_process->vm_space, VMA_MMAP_BASE, VMA_MMAP_BASE + VMA_MMAP_RANGE,
+                                  map_params->length, vm_flags);
         if (!vaddr) {
             return -OR_ENOMEM;
         }
@@ -321,6 +405,54 @@ int64_t sys_port_recv_impl(or_msg_recv_t* msg) {
                            msg->buffer_size, msg->timeout_ns);
 }
 
+static bool user_buffer_valid(const void* buf, uint64_t size) {
+    return size == 0 ||
+           (buf && mmu_is_valid_addr((uint64_t)buf) && mmu_is_valid_addr((uint64_t)buf + size - 1));
+}
+
+// sys_port_call - synchronous request/reply round trip
+int64_t sys_port_call_impl(or_cap_t port, const void* request, uint64_t request_size, void* reply,
+                           uint64_t reply_size, uint64_t timeout_ns) {
+    if (!user_buffer_valid(request, request_size) || !user_buffer_valid(reply, reply_size)) {
+        return -OR_EFAULT;
+    }
+    return ipc_call(port, request, request_size, reply, reply_size, timeout_ns);
+}
+
+// sys_port_reply_recv - answer the current call and wait for the next
+int64_t sys_port_reply_recv_impl(or_cap_t port, const void* reply, uint64_t reply_size, void* request,
+                                 uint64_t request_size) {
+    if (!user_buffer_valid(reply, reply_size) || !request || !user_buffer_valid(request, request_size)) {
+        return -OR_EFAULT;
+    }
+    return ipc_reply_recv(port, reply, reply_size, request, request_size);
+}
+
+// Statistics of the next IPC port from `cursor` on (0 for the first);
+// returns the cursor to pass for the one after. Sandboxed processes and
+// those in a PID namespace only see their own ports
+int64_t sys_ipc_stats_impl(uint64_t cursor, ipc_port_stats_t* stats) {
+    if (!stats || !mmu_is_valid_addr((uint64_t)stats) ||
+        !mmu_is_valid_addr((uint64_t)stats + sizeof(*stats) - 1)) {
+        return -OR_EFAULT;
+    }
+
+    process_t* caller = scheduler_get_current_process();
+    if (!caller) {
+        return -OR_EINVAL;
+    }
+    bool confined = security_is_sandboxed(caller->pid) || ns_in_child_pid_ns(caller->pid);
+
+    ipc_port_stats_t kernel_stats;
+    int64_t next = ipc_get_port_stats(cursor, confined ? caller->pid : 0, &kernel_stats);
+    if (next < 0) {
+        return next;
+    }
+    kernel_stats.owner_pid = ns_pid_to_viewer(caller->pid, kernel_stats.owner_pid);
+    *stats = kernel_stats;
+    return next;
+}
+
 // ========================================
 // NEW SYSTEM CALL IMPLEMENTATIONS
 // ========================================
@@ -339,8 +471,25 @@ int64_t sys_proc_create_impl(const char* executable_path, char* const argv[], ch
         return -OR_ENOMEM;
     }
     
+    // It is a child of its creator, which can wait for it, and starts in
+    // its namespaces with its MAC label, unless its program has one
+    process_t* current_process = scheduler_get_current_process();
+    new_process->parent = current_process;
+    uint64_t creator = current_process ? current_process->pid : 0;
+    int result = ns_process_fork(creator, new_process->pid);
+    if (result == OR_OK) {
+        result = mac_process_fork(creator, new_process->pid);
+    }
+    if (result == OR_OK) {
+        result = mac_process_exec(new_process->pid, executable_path);
+    }
+    if (result != OR_OK) {
+        scheduler_destroy_process(new_process);
+        return result;
+    }
+    
     // Load ELF executable
-    int result = elf_load_process(new_process, executable_path);
+    result = elf_load_process(new_process, executable_path);
     if (result != OR_OK) {
         scheduler_destroy_process(new_process);
         return result;
@@ -357,7 +506,68 @@ int64_t sys_proc_create_impl(const char* executable_path, char* const argv[], ch
     scheduler_add_process(new_process);
     
     kdebug("Created process PID %llu", (unsigned long long)new_process->pid);
-    return (int64_t)new_process->pid;
+    return current_process ? (int64_t)ns_pid_to_viewer(current_process->pid, new_process->pid)
+                           : (int64_t)new_process->pid;
+}
+
+// sys_proc_fork - fork the calling process; the child starts at
+// entry_point on stack_pointer, both addresses of the shared layout
+int64_t sys_proc_fork_impl(uint64_t entry_point, uint64_t stack_pointer, uint64_t arg) {
+    process_t* current_process = scheduler_get_current_process();
+    if (!current_process || !entry_point || !stack_pointer) {
+        return -OR_EINVAL;
+    }
+    
+    process_t* child = scheduler_fork_process(current_process, entry_point, stack_pointer, arg);
+    if (!child) {
+        return -OR_ENOMEM;
+    }
+    
+    scheduler_add_process(child);
+    
+    kdebug("sys_proc_fork: PID %llu forked into PID %llu",
+           (unsigned long long)current_process->pid, (unsigned long long)child->pid);
+    return (int64_t)ns_pid_to_viewer(current_process->pid, child->pid);
+}
+
+// sys_proc_info - describe the process with the lowest PID above after_pid
+int64_t sys_proc_info_impl(uint64_t after_pid, proc_info_t* info) {
+    if (!info || !mmu_is_valid_addr((uint64_t)info)) {
+        return -OR_EFAULT;
+    }
+    
+    proc_info_t kernel_info;
+    process_t* caller = scheduler_get_current_process();
+    if (!caller || !ns_in_child_pid_ns(caller->pid)) {
+        int result = scheduler_get_process_info(after_pid, &kernel_info);
+        if (result != OR_OK) {
+            return result;
+        }
+        *info = kernel_info;
+        return (int64_t)kernel_info.pid;
+    }
+    
+    // Inside a PID namespace: the processes it sees, in its numbering. The
+    // walk is in global order, so keep the lowest local PID above after_pid
+    proc_info_t found;
+    memset(&found, 0, sizeof(found));
+    uint64_t best = 0;
+    uint64_t pid = 0;
+    while (scheduler_get_process_info(pid, &kernel_info) == OR_OK) {
+        pid = kernel_info.pid;
+        uint64_t local = ns_pid_to_viewer(caller->pid, pid);
+        if (local > after_pid && (!best || local < best)) {
+            best = local;
+            found = kernel_info;
+        }
+    }
+    if (!best) {
+        return -OR_ENOENT;
+    }
+    found.pid = best;
+    found.ppid = ns_pid_to_viewer(caller->pid, found.ppid);
+    *info = found;
+    return (int64_t)best;
 }
 
 // sys_thread_create - create new thread
@@ -388,7 +598,7 @@ int64_t sys_wait_impl(uint64_t pid, int* status, uint64_t options) {
         return -OR_EINVAL;
     }
     
-    process_t* target = scheduler_find_process(pid);
+    process_t* target = scheduler_find_process(ns_pid_from_viewer(current_process->pid, pid));
     if (!target || target->parent != current_process) {
         return -OR_EINVAL; // Not our child
     }
@@ -404,7 +614,7 @@ int64_t sys_wait_impl(uint64_t pid, int* status, uint64_t options) {
         *status = target->exit_code;
     }
     
-    uint64_t waited_pid = target->pid;
+    uint64_t waited_pid = pid;
     
     // Clean up zombie process
     scheduler_destroy_process(target);
@@ -417,6 +627,11 @@ int64_t sys_signal_impl(uint64_t target_pid, uint32_t signal_num) {
     kdebug("sys_signal called: target=%llu, signal=%u", 
            (unsigned long long)target_pid, signal_num);
     
+    // Processes outside the caller's PID namespace do not exist for it
+    process_t* current_process = scheduler_get_current_process();
+    if (current_process) {
+        target_pid = ns_pid_from_viewer(current_process->pid, target_pid);
+    }
     process_t* target = scheduler_find_process(target_pid);
     if (!target) {
         return -OR_ENOENT;
@@ -428,7 +643,7 @@ int64_t sys_signal_impl(uint64_t target_pid, uint32_t signal_num) {
 // sys_getpid - get current process ID
 int64_t sys_getpid_impl(void) {
     process_t* current_process = scheduler_get_current_process();
-    return current_process ? (int64_t)current_process->pid : -OR_EINVAL;
+    return current_process ? (int64_t)ns_pid_to_viewer(current_process->pid, current_process->pid) : -OR_EINVAL;
 }
 
 // sys_gettid - get current thread ID  
@@ -449,7 +664,7 @@ int64_t sys_vm_unmap_impl(uint64_t addr, size_t length) {
     }
     
     size_t pages = length / PAGE_SIZE;
-    vmm_free_pages(current_process->vm_space, addr, pages);
+    aslr_free_guarded(current_process->vm_space, addr, pages);
     
     kdebug("sys_vm_unmap: unmapped %llu pages at 0x%p", 
            (unsigned long long)pages, (void*)addr);
@@ -475,15 +690,22 @@ int64_t sys_vm_protect_impl(uint64_t addr, size_t length, uint32_t new_prot) {
     if (new_prot & VM_PROT_EXEC) vm_flags |= VM_FLAG_EXEC;
     if (!current_process->vm_space->is_kernel) vm_flags |= VM_FLAG_USER;
     
-    size_t pages = length / PAGE_SIZE;
-    for (size_t i = 0; i < pages; i++) {
-        uint64_t page_addr = addr + i * PAGE_SIZE;
-        if (vmm_protect_page(current_process->vm_space, page_addr, vm_flags) != OR_OK) {
-            return -OR_EINVAL;
+    // Splits the areas at the bounds; pages not touched yet pick the new
+    // protection up when they fault in
+    int result = vma_protect(current_process->vm_space, addr, length, vm_flags);
+    if (result == -OR_EFAULT) {
+        // Not covered by areas: mappings made before area tracking
+        size_t pages = length / PAGE_SIZE;
+        for (size_t i = 0; i < pages; i++) {
+            uint64_t page_addr = addr + i * PAGE_SIZE;
+            if (vmm_protect_page(current_process->vm_space, page_addr, vm_flags) != OR_OK) {
+                return -OR_EINVAL;
+            }
         }
+        return OR_OK;
     }
     
-    return OR_OK;
+    return result;
 }
 
 // Stubs for remaining system calls (to be implemented later)
@@ -503,8 +725,31 @@ int64_t sys_shm_detach_impl(uint64_t addr) {
 }
 
 int64_t sys_madvise_impl(uint64_t addr, size_t length, uint32_t advice) {
-    (void)addr; (void)length; (void)advice;
-    return -OR_ENOSYS;
+    if (length == 0 || !IS_ALIGNED(addr, PAGE_SIZE)) {
+        return -OR_EINVAL;
+    }
+    
+    process_t* current_process = scheduler_get_current_process();
+    if (!current_process || !current_process->vm_space) {
+        return -OR_EINVAL;
+    }
+    
+    size_t pages = (length + PAGE_SIZE - 1) / PAGE_SIZE;
+    
+    switch (advice) {
+    case OR_MADV_NORMAL:
+        return OR_OK;
+    case OR_MADV_LOCK:
+        return vmm_lock_range(current_process->vm_space, addr, pages, true);
+    case OR_MADV_UNLOCK:
+        return vmm_lock_range(current_process->vm_space, addr, pages, false);
+    case OR_MADV_MERGEABLE:
+        return ksm_advise(current_process->vm_space, addr, length, true);
+    case OR_MADV_UNMERGEABLE:
+        return ksm_advise(current_process->vm_space, addr, length, false);
+    default:
+        return -OR_EINVAL;
+    }
 }
 
 int64_t sys_port_share_impl(or_cap_t port, uint64_t target_pid) {
@@ -517,13 +762,342 @@ int64_t sys_msg_forward_impl(or_cap_t source_port, or_cap_t dest_port) {
     return -OR_ENOSYS;
 }
 
+// Monotonic and boot time are read through the caller's time namespace
 int64_t sys_clock_get_impl(uint32_t clock_id, uint64_t* timestamp) {
     if (!timestamp || !mmu_is_valid_addr((uint64_t)timestamp)) {
         return -OR_EFAULT;
     }
-    
-    *timestamp = arch_get_timestamp();
-    return OR_OK;
+
+    process_t* caller = scheduler_get_current_process();
+    ns_clock_offsets_t offsets = {0, 0};
+    if (caller) {
+        ns_clock_offsets(caller->pid, &offsets);
+    }
+
+    switch (clock_id) {
+    case CLOCK_ID_MONOTONIC:
+        *timestamp = wallclock_monotonic_ns() + (uint64_t)offsets.monotonic;
+        return OR_OK;
+    case CLOCK_ID_BOOTTIME:
+        *timestamp = wallclock_boottime_ns() + (uint64_t)offsets.boottime;
+        return OR_OK;
+    case CLOCK_ID_REALTIME:
+        *timestamp = wallclock_realtime_ns();
+        return OR_OK;
+    default:
+        return -OR_EINVAL;
+    }
+}
+
+// Discipline the wall clock. Sandboxed processes need SYS_CLOCK_ADJUST in
+// their profile, which only the NTP service's manifest grants
+int64_t sys_clock_adjust_impl(uint32_t op, uint64_t arg) {
+    switch (op) {
+    case CLOCK_ADJUST_SLEW:
+        return wallclock_slew((int64_t)arg);
+    case CLOCK_ADJUST_STEP:
+        return wallclock_step((int64_t)arg);
+    case CLOCK_ADJUST_LEAP:
+        return wallclock_set_leap((uint32_t)arg);
+    case CLOCK_ADJUST_STATUS: {
+        wallclock_status_t* status = (wallclock_status_t*)arg;
+        if (!status || !mmu_is_valid_addr((uint64_t)status)) {
+            return -OR_EFAULT;
+        }
+        wallclock_status_t kernel_status;
+        wallclock_get_status(&kernel_status);
+        memcpy(status, &kernel_status, sizeof(kernel_status));
+        return OR_OK;
+    }
+    default:
+        return -OR_EINVAL;
+    }
+}
+
+// Copy a NUL-terminated string of at most `size` bytes (NUL included)
+static int copy_user_string(char* dest, const char* src, size_t size) {
+    for (size_t i = 0; i < size; i++) {
+        if (!mmu_is_valid_addr((uint64_t)(src + i))) {
+            return -OR_EFAULT;
+        }
+        dest[i] = src[i];
+        if (dest[i] == '\0') {
+            return OR_OK;
+        }
+    }
+    return -OR_EINVAL;
+}
+
+// Show or store a cpufreq policy attribute. Sandboxed processes need
+// SYS_CPUFREQ in their profile
+int64_t sys_cpufreq_impl(uint32_t op, uint32_t cpu, const char* name, char* buf, uint64_t size) {
+    char attr[CPUFREQ_ATTR_NAME_MAX];
+    char value[CPUFREQ_ATTR_VALUE_MAX];
+    if (!name || !buf || size == 0) {
+        return -OR_EINVAL;
+    }
+    int result = copy_user_string(attr, name, sizeof(attr));
+    if (result != OR_OK) {
+        return result;
+    }
+
+    switch (op) {
+    case CPUFREQ_OP_SHOW:
+        if (!mmu_is_valid_addr((uint64_t)buf) || !mmu_is_valid_addr((uint64_t)buf + size - 1)) {
+            return -OR_EFAULT;
+        }
+        result = cpufreq_attr_show(cpu, attr, value, sizeof(value));
+        if (result < 0) {
+            return result;
+        }
+        if ((uint64_t)result >= size) {
+            return -OR_EINVAL;
+        }
+        memcpy(buf, value, (size_t)result + 1);
+        return result;
+    case CPUFREQ_OP_STORE:
+        result = copy_user_string(value, buf, size < sizeof(value) ? (size_t)size : sizeof(value));
+        if (result != OR_OK) {
+            return result;
+        }
+        return cpufreq_attr_store(cpu, attr, value);
+    default:
+        return -OR_EINVAL;
+    }
+}
+
+// Out-of-memory policy. Any process may read group information and move
+// itself to another group; sandboxed processes can do nothing else, so
+// the policy agent and the tools that configure groups run unsandboxed
+int64_t sys_oom_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2, uint64_t arg3) {
+    process_t* caller = scheduler_get_current_process();
+    if (!caller) {
+        return -OR_EINVAL;
+    }
+    bool sandboxed = security_is_sandboxed(caller->pid);
+
+    switch (op) {
+    case OOM_CTL_SET_AGENT:
+        if (sandboxed) {
+            return -OR_EPERM;
+        }
+        return oom_set_agent(caller->pid, (or_cap_t)arg1, arg2);
+    case OOM_CTL_GROUP_CREATE: {
+        char name[OOM_GROUP_NAME_MAX];
+        if (sandboxed) {
+            return -OR_EPERM;
+        }
+        int result = copy_user_string(name, (const char*)arg1, sizeof(name));
+        if (result != OR_OK) {
+            return result;
+        }
+        return oom_group_create(name, (int32_t)arg2, (uint32_t)arg3);
+    }
+    case OOM_CTL_GROUP_SET:
+        if (sandboxed) {
+            return -OR_EPERM;
+        }
+        return oom_group_set((uint32_t)arg1, (int32_t)arg2, (uint32_t)arg3);
+    case OOM_CTL_GROUP_SLACK:
+        if (sandboxed) {
+            return -OR_EPERM;
+        }
+        return oom_group_set_timer_slack((uint32_t)arg1, arg2);
+    case OOM_CTL_GROUP_ATTACH: {
+        uint64_t pid = arg2 ? arg2 : caller->pid;
+        if (sandboxed && pid != caller->pid) {
+            return -OR_EPERM;
+        }
+        // Joining a protected group is how a process would escape the killer
+        oom_group_info_t info;
+        int result = oom_group_get_info((uint32_t)arg1, &info);
+        if (result != OR_OK) {
+            return result;
+        }
+        if (sandboxed && (info.flags & OOM_GROUP_PROTECTED)) {
+            return -OR_EPERM;
+        }
+        return oom_group_attach((uint32_t)arg1, pid);
+    }
+    case OOM_CTL_GROUP_INFO: {
+        oom_group_info_t* info = (oom_group_info_t*)arg2;
+        if (!info || !mmu_is_valid_addr((uint64_t)info) ||
+            !mmu_is_valid_addr((uint64_t)info + sizeof(*info) - 1)) {
+            return -OR_EFAULT;
+        }
+        oom_group_info_t kernel_info;
+        int result = oom_group_get_info((uint32_t)arg1, &kernel_info);
+        if (result != OR_OK) {
+            return result;
+        }
+        *info = kernel_info;
+        return OR_OK;
+    }
+    case OOM_CTL_STATS: {
+        oom_stats_t* stats = (oom_stats_t*)arg1;
+        if (!stats || !mmu_is_valid_addr((uint64_t)stats) ||
+            !mmu_is_valid_addr((uint64_t)stats + sizeof(*stats) - 1)) {
+            return -OR_EFAULT;
+        }
+        oom_get_stats(stats);
+        return OR_OK;
+    }
+    default:
+        return -OR_EINVAL;
+    }
+}
+
+// Same-page merging. Anyone may read the configuration and statistics;
+// turning the scanner on or retuning it is for unsandboxed processes,
+// since it spends CPU time on behalf of every process that opted in
+int64_t sys_ksm_ctl_impl(uint32_t op, uint64_t arg) {
+    process_t* caller = scheduler_get_current_process();
+    if (!caller) {
+        return -OR_EINVAL;
+    }
+
+    switch (op) {
+    case KSM_CTL_GET_CONFIG: {
+        ksm_config_t* config = (ksm_config_t*)arg;
+        if (!config || !mmu_is_valid_addr((uint64_t)config) ||
+            !mmu_is_valid_addr((uint64_t)config + sizeof(*config) - 1)) {
+            return -OR_EFAULT;
+        }
+        return ksm_get_config(config);
+    }
+    case KSM_CTL_SET_CONFIG: {
+        const ksm_config_t* config = (const ksm_config_t*)arg;
+        if (security_is_sandboxed(caller->pid)) {
+            return -OR_EPERM;
+        }
+        if (!config || !mmu_is_valid_addr((uint64_t)config) ||
+            !mmu_is_valid_addr((uint64_t)config + sizeof(*config) - 1)) {
+            return -OR_EFAULT;
+        }
+        ksm_config_t kernel_config = *config;
+        return ksm_set_config(&kernel_config);
+    }
+    case KSM_CTL_STATS: {
+        ksm_stats_t* stats = (ksm_stats_t*)arg;
+        if (!stats || !mmu_is_valid_addr((uint64_t)stats) ||
+            !mmu_is_valid_addr((uint64_t)stats + sizeof(*stats) - 1)) {
+            return -OR_EFAULT;
+        }
+        ksm_get_stats(stats);
+        return OR_OK;
+    }
+    default:
+        return -OR_EINVAL;
+    }
+}
+
+// Namespaces: move a child not started yet into new ones, offset the
+// clocks of its time namespace, or tell which ones a process is in.
+// Servers keeping per-namespace state (fs, net) ask for the namespaces of
+// their senders
+int64_t sys_ns_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2) {
+    process_t* caller = scheduler_get_current_process();
+    if (!caller) {
+        return -OR_EINVAL;
+    }
+
+    switch (op) {
+    case NS_CTL_UNSHARE: {
+        uint64_t pid = arg1 ? arg1 : caller->pid;
+        if (security_is_sandboxed(caller->pid)) {
+            return -OR_EPERM;
+        }
+        process_t* target = scheduler_find_process(pid);
+        if (!target) {
+            return -OR_ENOENT;
+        }
+        if (target != caller && target->parent != caller) {
+            return -OR_EPERM;
+        }
+        // A running process keeps its number and its clocks: only a
+        // child gets a new PID namespace, as its PID 1, or time namespace
+        if (target == caller && (arg2 & (NS_PID | NS_TIME))) {
+            return -OR_EINVAL;
+        }
+        return ns_unshare(pid, (uint32_t)arg2);
+    }
+    case NS_CTL_CLOCKS: {
+        const ns_clock_offsets_t* offsets = (const ns_clock_offsets_t*)arg2;
+        if (security_is_sandboxed(caller->pid)) {
+            return -OR_EPERM;
+        }
+        process_t* target = scheduler_find_process(arg1);
+        if (!target) {
+            return -OR_ENOENT;
+        }
+        if (target->parent != caller) {
+            return -OR_EPERM;
+        }
+        if (!offsets || !mmu_is_valid_addr((uint64_t)offsets) ||
+            !mmu_is_valid_addr((uint64_t)offsets + sizeof(*offsets) - 1)) {
+            return -OR_EFAULT;
+        }
+        ns_clock_offsets_t kernel_offsets = *offsets;
+        return ns_set_clock_offsets(arg1, &kernel_offsets);
+    }
+    case NS_CTL_INFO: {
+        ns_info_t* info = (ns_info_t*)arg2;
+        if (!info || !mmu_is_valid_addr((uint64_t)info) ||
+            !mmu_is_valid_addr((uint64_t)info + sizeof(*info) - 1)) {
+            return -OR_EFAULT;
+        }
+        ns_info_t kernel_info;
+        int result = ns_get_info(arg1 ? arg1 : caller->pid, &kernel_info);
+        if (result != OR_OK) {
+            return result;
+        }
+        *info = kernel_info;
+        return OR_OK;
+    }
+    default:
+        return -OR_EINVAL;
+    }
+}
+
+// Hardware virtualization for a VMM: VMs, their memory and vCPUs, see
+// hypervisor.h. Sandboxed processes need SYS_HV_CTL in their profile
+int64_t sys_hv_ctl_impl(uint32_t op, uint32_t vm, uint32_t vcpu, uint64_t arg) {
+    return hv_ctl(op, vm, vcpu, arg);
+}
+
+// Read or set the scheduling policy of a thread of the calling process,
+// 0 being the calling thread. Sandboxed processes need SYS_SCHED_ATTR in
+// their profile
+int64_t sys_sched_attr_impl(uint32_t op, uint64_t tid, sched_rt_attr_t* attr) {
+    if (!attr || !mmu_is_valid_addr((uint64_t)attr) ||
+        !mmu_is_valid_addr((uint64_t)attr + sizeof(*attr) - 1)) {
+        return -OR_EFAULT;
+    }
+
+    process_t* caller = scheduler_get_current_process();
+    thread_t* thread = tid ? scheduler_find_thread(tid) : scheduler_get_current_thread();
+    if (!caller || !thread) {
+        return -OR_ENOENT;
+    }
+    if (thread->parent_process != caller) {
+        return -OR_EPERM;
+    }
+
+    sched_rt_attr_t value;
+    switch (op) {
+    case SCHED_ATTR_OP_GET: {
+        int result = sched_rt_getattr(thread, &value);
+        if (result == OR_OK) {
+            memcpy(attr, &value, sizeof(value));
+        }
+        return result;
+    }
+    case SCHED_ATTR_OP_SET:
+        memcpy(&value, attr, sizeof(value));
+        return sched_rt_setattr(thread, &value);
+    default:
+        return -OR_EINVAL;
+    }
 }
 
 int64_t sys_timer_create_impl(uint32_t clock_id, uint64_t* timer_id) {
@@ -541,7 +1115,15 @@ int64_t sys_timer_stop_impl(uint64_t timer_id) {
     return -OR_ENOSYS;
 }
 
+// Sleeps of a group with timer slack end on a multiple of it, so that
+// its members sleeping around the same time wake together
 int64_t sys_nanosleep_impl(uint64_t nanoseconds) {
+    process_t* caller = scheduler_get_current_process();
+    uint64_t slack = caller ? oom_timer_slack(caller->pid) : 0;
+    if (slack && nanoseconds) {
+        uint64_t wake = wallclock_monotonic_ns() + nanoseconds;
+        nanoseconds += (slack - wake % slack) % slack;
+    }
     scheduler_sleep_ns(nanoseconds);
     return OR_OK;
 }
@@ -603,14 +1185,137 @@ int64_t sys_cap_query_impl(or_cap_t cap, uint32_t* permissions) {
     return -OR_ENOSYS;
 }
 
-int64_t sys_sandbox_load_impl(const char* policy_path) {
-    (void)policy_path;
-    return -OR_ENOSYS;
+// Attach a sandbox profile to a process. Any process may sandbox itself;
+// only unsandboxed (system) processes may sandbox others
+int64_t sys_sandbox_load_impl(uint64_t pid, const sandbox_profile_t* profile) {
+    if (!profile || !mmu_is_valid_addr((uint64_t)profile)) {
+        return -OR_EINVAL;
+    }
+    
+    process_t* current_process = scheduler_get_current_process();
+    if (!current_process) {
+        return -OR_EINVAL;
+    }
+    if (pid != current_process->pid && security_is_sandboxed(current_process->pid)) {
+        return -OR_EPERM;
+    }
+    
+    sandbox_profile_t kernel_profile;
+    memcpy(&kernel_profile, profile, sizeof(kernel_profile));
+    
+    return security_sandbox_apply(pid, &kernel_profile);
 }
 
 int64_t sys_audit_emit_impl(uint32_t event_type, const void* event_data, size_t data_size) {
-    (void)event_type; (void)event_data; (void)data_size;
-    return -OR_ENOSYS;
+    char description[AUDIT_EMIT_MAX_BYTES];
+    
+    if (!event_data || !mmu_is_valid_addr((uint64_t)event_data) || data_size == 0) {
+        return -OR_EINVAL;
+    }
+    
+    // Records are free-form text; longer ones are truncated
+    if (data_size > AUDIT_EMIT_MAX_BYTES - 1) {
+        data_size = AUDIT_EMIT_MAX_BYTES - 1;
+    }
+    memcpy(description, event_data, data_size);
+    description[data_size] = '\0';
+    
+    return security_audit_user_event(event_type, description);
+}
+
+// Mandatory access control, see mac.h. Loading a policy or changing the
+// mode takes an unsandboxed caller the current policy lets load it;
+// checks are for the servers enforcing it
+int64_t sys_mac_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2) {
+    process_t* caller = scheduler_get_current_process();
+    if (!caller) {
+        return -OR_EINVAL;
+    }
+
+    switch (op) {
+    case MAC_CTL_LOAD:
+    case MAC_CTL_SET_MODE: {
+        if (security_is_sandboxed(caller->pid)) {
+            return -OR_EPERM;
+        }
+        int result = mac_check(caller->pid, MAC_CLASS_POLICY, "policy", MAC_PERM_LOAD);
+        if (result != OR_OK) {
+            return result;
+        }
+        if (op == MAC_CTL_SET_MODE) {
+            return mac_set_mode((uint32_t)arg1);
+        }
+        if (!arg1 || arg2 < sizeof(mac_policy_header_t) || arg2 > MAC_POLICY_MAX_SIZE ||
+            !mmu_is_valid_addr(arg1) || !mmu_is_valid_addr(arg1 + arg2 - 1)) {
+            return -OR_EFAULT;
+        }
+        void* policy = kmalloc(arg2);
+        if (!policy) {
+            return -OR_ENOMEM;
+        }
+        memcpy(policy, (const void*)arg1, arg2);
+        result = mac_load(policy, arg2);
+        kfree(policy);
+        return result;
+    }
+    case MAC_CTL_CHECK:
+    case MAC_CTL_RELABEL: {
+        const mac_request_t* request = (const mac_request_t*)arg1;
+        if (!request || !mmu_is_valid_addr(arg1) || !mmu_is_valid_addr(arg1 + sizeof(*request) - 1)) {
+            return -OR_EFAULT;
+        }
+        mac_request_t kernel_request;
+        memcpy(&kernel_request, request, sizeof(kernel_request));
+        kernel_request.name[MAC_PATTERN_MAX - 1] = '\0';
+        uint64_t pid = ns_pid_from_viewer(caller->pid, kernel_request.pid);
+        if (!pid || !scheduler_find_process(pid)) {
+            return -OR_ENOENT;
+        }
+        if (op == MAC_CTL_CHECK) {
+            return mac_check(pid, kernel_request.kind, kernel_request.name, kernel_request.perms);
+        }
+        return mac_relabel(caller->pid, pid, kernel_request.kind, kernel_request.name);
+    }
+    case MAC_CTL_LABEL: {
+        char* name = (char*)arg2;
+        if (!name || !mmu_is_valid_addr(arg2) || !mmu_is_valid_addr(arg2 + MAC_LABEL_NAME_MAX - 1)) {
+            return -OR_EFAULT;
+        }
+        uint64_t pid = arg1 ? ns_pid_from_viewer(caller->pid, arg1) : caller->pid;
+        if (!pid || !scheduler_find_process(pid)) {
+            return -OR_ENOENT;
+        }
+        char label[MAC_LABEL_NAME_MAX];
+        mac_label_of(pid, label);
+        memcpy(name, label, sizeof(label));
+        return OR_OK;
+    }
+    case MAC_CTL_DENIAL: {
+        mac_denial_t* denial = (mac_denial_t*)arg2;
+        if (!denial || !mmu_is_valid_addr(arg2) || !mmu_is_valid_addr(arg2 + sizeof(*denial) - 1)) {
+            return -OR_EFAULT;
+        }
+        mac_denial_t kernel_denial;
+        int result = mac_get_denial((uint32_t)arg1, &kernel_denial);
+        if (result != OR_OK) {
+            return result;
+        }
+        *denial = kernel_denial;
+        return OR_OK;
+    }
+    case MAC_CTL_STATUS: {
+        mac_status_t* status = (mac_status_t*)arg1;
+        if (!status || !mmu_is_valid_addr(arg1) || !mmu_is_valid_addr(arg1 + sizeof(*status) - 1)) {
+            return -OR_EFAULT;
+        }
+        mac_status_t kernel_status;
+        mac_get_status(&kernel_status);
+        *status = kernel_status;
+        return OR_OK;
+    }
+    default:
+        return -OR_EINVAL;
+    }
 }
 
 int64_t sys_dbg_trace_impl(uint32_t trace_type, const void* trace_data, size_t data_size) {
@@ -623,18 +1328,96 @@ int64_t sys_random_impl(void* buffer, size_t size) {
         return -OR_EINVAL;
     }
     
-    // Simple pseudo-random number generator for now
-    static uint64_t rand_state = 0x123456789ABCDEF0ULL;
+    // Draw from the kernel entropy pool; applications that need blocking
+    // until-seeded semantics use the entropy server's getrandom IPC instead
+    if (size > SYS_RANDOM_MAX_BYTES) {
+        size = SYS_RANDOM_MAX_BYTES;
+    }
     
     uint8_t* bytes = (uint8_t*)buffer;
-    for (size_t i = 0; i < size; i++) {
-        rand_state = rand_state * 1103515245 + 12345;
-        bytes[i] = (uint8_t)(rand_state >> 32);
+    size_t offset = 0;
+    while (offset < size) {
+        uint64_t word = security_get_random();
+        size_t chunk = (size - offset) < sizeof(word) ? (size - offset) : sizeof(word);
+        memcpy(bytes + offset, &word, chunk);
+        offset += chunk;
     }
     
     return (int64_t)size;
 }
 
+// Copy one measured boot event log entry; used by the TPM driver to replay
+// kernel measurements into the PCRs
+int64_t sys_measure_log_impl(uint32_t index, measure_event_t* event) {
+    if (!event || !mmu_is_valid_addr((uint64_t)event)) {
+        return -OR_EINVAL;
+    }
+    
+    int result = measure_log_get(index, event);
+    if (result != OR_OK) {
+        return result;
+    }
+    
+    return (int64_t)measure_log_count();
+}
+
+// Describe the framebuffer the loader left behind; used by the fallback
+// framebuffer driver when no GPU driver owns the display
+int64_t sys_boot_framebuffer_impl(boot_framebuffer_t* out) {
+    if (!out || !mmu_is_valid_addr((uint64_t)out)) {
+        return -OR_EINVAL;
+    }
+    
+    const boot_info_t* boot = boot_info_get();
+    if (!boot || !boot->has_framebuffer) {
+        return -OR_ENODEV;
+    }
+    
+    memcpy(out, &boot->framebuffer, sizeof(*out));
+    return OR_OK;
+}
+
+// Early console handoff. The console server reads the text geometry,
+// cursor and scrollback, then releases the early console so it stops
+// drawing on the framebuffer; output keeps being recorded, so the server
+// reads whatever was written in between. Only an unsandboxed process
+// may take the display away
+int64_t sys_earlycon_ctl_impl(uint32_t op, uint64_t arg1, uint64_t arg2, uint64_t arg3) {
+    process_t* caller = scheduler_get_current_process();
+    if (!caller) {
+        return -OR_EINVAL;
+    }
+
+    switch (op) {
+    case EARLYCON_CTL_STATUS: {
+        earlycon_status_t* status = (earlycon_status_t*)arg1;
+        if (!status || !mmu_is_valid_addr((uint64_t)status) ||
+            !mmu_is_valid_addr((uint64_t)status + sizeof(*status) - 1)) {
+            return -OR_EFAULT;
+        }
+        earlycon_status(status);
+        return OR_OK;
+    }
+    case EARLYCON_CTL_READ: {
+        char* buffer = (char*)arg2;
+        if (arg3 == 0) {
+            return 0;
+        }
+        if (!buffer || !mmu_is_valid_addr((uint64_t)buffer) || !mmu_is_valid_addr((uint64_t)buffer + arg3 - 1)) {
+            return -OR_EFAULT;
+        }
+        return (int64_t)earlycon_read(arg1, buffer, (size_t)arg3);
+    }
+    case EARLYCON_CTL_RELEASE:
+        if (security_is_sandboxed(caller->pid)) {
+            return -OR_EPERM;
+        }
+        return (int64_t)earlycon_release();
+    default:
+        return -OR_EINVAL;
+    }
+}
+
 // Initialize system call interface
 void syscalls_init(void) {
     kinfo("Initializing system call interface");
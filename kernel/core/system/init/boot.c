@@ -215,8 +215,10 @@ int orion_boot_validate(const struct orion_boot_info *boot_info)
         return 0;
     }
 
-    // Validate checksums
-    uint32_t calculated_header_checksum = orion_checksum(boot_info, sizeof(struct orion_boot_info) - sizeof(uint32_t));
+    // Validate checksums, the header one being computed with its own field zeroed
+    struct orion_boot_info header = *boot_info;
+    header.header_checksum = 0;
+    uint32_t calculated_header_checksum = orion_checksum(&header, sizeof(struct orion_boot_info));
     if (calculated_header_checksum != boot_info->header_checksum)
     {
         kerror("Boot info header checksum mismatch: 0x%x vs 0x%x",
@@ -260,6 +262,12 @@ int orion_boot_validate(const struct orion_boot_info *boot_info)
             return 0;
         }
 
+        // The end tag closes the list and is not counted
+        if (header->type == ORION_INFO_END)
+        {
+            break;
+        }
+
         structure_count++;
         offset += header->size;
     }
@@ -323,6 +331,18 @@ void orion_boot_debug_print(const struct orion_boot_info *boot_info)
             case ORION_INFO_EFI:
                 type_name = "EFI System Info";
                 break;
+            case ORION_INFO_MODULES:
+                type_name = "Boot Modules";
+                break;
+            case ORION_INFO_FRAMEBUFFER:
+                type_name = "Framebuffer";
+                break;
+            case ORION_INFO_ACPI:
+                type_name = "ACPI RSDP";
+                break;
+            case ORION_INFO_END:
+                type_name = "End";
+                break;
             default:
                 type_name = "Unknown";
                 break;
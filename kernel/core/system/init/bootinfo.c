@@ -0,0 +1,680 @@
+/*
+ * Orion Operating System - Boot Information
+ *
+ * Parsers for the three ways the kernel can be entered, all filling the
+ * same boot_info_t (see bootinfo.h). Multiboot2 loaders leave their magic
+ * in EAX and the information address in RBX, which the x86_64 entry code
+ * saves in boot_protocol_magic and boot_protocol_data; Limine answers the
+ * requests placed in the .limine_requests section. Otherwise the kernel
+ * was started by the Orion loader, or by the device tree boot code that
+ * builds the same tagged structure.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include "bootinfo.h"
+
+#define MULTIBOOT2_BOOTLOADER_MAGIC 0x36D76289
+
+// Multiboot2 information tags
+#define MB2_TAG_END 0
+#define MB2_TAG_CMDLINE 1
+#define MB2_TAG_LOADER_NAME 2
+#define MB2_TAG_MODULE 3
+#define MB2_TAG_MMAP 6
+#define MB2_TAG_FRAMEBUFFER 8
+#define MB2_TAG_ACPI_OLD 14
+#define MB2_TAG_ACPI_NEW 15
+
+#define MB2_FRAMEBUFFER_RGB 1
+
+// Limine memory map types
+#define LIMINE_MEMMAP_USABLE 0
+#define LIMINE_MEMMAP_RESERVED 1
+#define LIMINE_MEMMAP_ACPI_RECLAIMABLE 2
+#define LIMINE_MEMMAP_ACPI_NVS 3
+#define LIMINE_MEMMAP_BAD_MEMORY 4
+#define LIMINE_MEMMAP_BOOTLOADER_RECLAIMABLE 5
+#define LIMINE_MEMMAP_KERNEL_AND_MODULES 6
+#define LIMINE_MEMMAP_FRAMEBUFFER 7
+
+#define LIMINE_FRAMEBUFFER_RGB 1
+
+#define LIMINE_COMMON_MAGIC 0xc7b1dd30df4c8b88, 0x0a82e883a194f07b
+
+// Largest RSDP (ACPI 2.0+), copied when Multiboot2 hands over a copy
+#define ACPI_RSDP_MAX 36
+
+typedef struct limine_request
+{
+    uint64_t id[4];
+    uint64_t revision;
+    void *volatile response;
+} limine_request_t;
+
+typedef struct
+{
+    uint64_t revision;
+    uint64_t offset;
+} limine_hhdm_response_t;
+
+typedef struct
+{
+    uint64_t revision;
+    const char *name;
+    const char *version;
+} limine_bootloader_info_response_t;
+
+typedef struct
+{
+    uint64_t base;
+    uint64_t length;
+    uint64_t type;
+} limine_memmap_entry_t;
+
+typedef struct
+{
+    uint64_t revision;
+    uint64_t entry_count;
+    limine_memmap_entry_t **entries;
+} limine_memmap_response_t;
+
+typedef struct
+{
+    void *address;
+    uint64_t width;
+    uint64_t height;
+    uint64_t pitch;
+    uint16_t bpp;
+    uint8_t memory_model;
+    uint8_t red_mask_size;
+    uint8_t red_mask_shift;
+    uint8_t green_mask_size;
+    uint8_t green_mask_shift;
+    uint8_t blue_mask_size;
+    uint8_t blue_mask_shift;
+} limine_framebuffer_t;
+
+typedef struct
+{
+    uint64_t revision;
+    uint64_t framebuffer_count;
+    limine_framebuffer_t **framebuffers;
+} limine_framebuffer_response_t;
+
+typedef struct
+{
+    uint64_t revision;
+    uint64_t address;
+} limine_rsdp_response_t;
+
+typedef struct
+{
+    uint64_t revision;
+    void *address;
+    uint64_t size;
+    const char *path;
+} limine_file_t;
+
+typedef struct
+{
+    uint64_t revision;
+    uint64_t module_count;
+    limine_file_t **modules;
+} limine_module_response_t;
+
+// Saved by the x86_64 entry code; other architectures have no Multiboot2
+extern uint32_t boot_protocol_magic __attribute__((weak));
+extern uint64_t boot_protocol_data __attribute__((weak));
+
+// Kernel image bounds from the linker script, where it provides them
+extern uint8_t __kernel_start[] __attribute__((weak));
+extern uint8_t __kernel_end[] __attribute__((weak));
+
+// Limine base revision 3: the loader zeroes the last word when it
+// supports it, and RSDP addresses are then physical
+__attribute__((used, section(".limine_requests"))) static volatile uint64_t limine_base_revision[3] = {
+    0xf9562b2d5c95a6c8, 0x6a7b384944536bdc, 3};
+
+__attribute__((used, section(".limine_requests"))) static volatile limine_request_t limine_hhdm_request = {
+    {LIMINE_COMMON_MAGIC, 0x48dcf1cb8ad2b852, 0x63984e959a98244b}, 0, NULL};
+__attribute__((used, section(".limine_requests"))) static volatile limine_request_t limine_bootloader_request = {
+    {LIMINE_COMMON_MAGIC, 0xf55038d8e2a1202f, 0x279426fcf5f59740}, 0, NULL};
+__attribute__((used, section(".limine_requests"))) static volatile limine_request_t limine_memmap_request = {
+    {LIMINE_COMMON_MAGIC, 0x67cf3d9d378a806f, 0xe304acdfc50c3c62}, 0, NULL};
+__attribute__((used, section(".limine_requests"))) static volatile limine_request_t limine_framebuffer_request = {
+    {LIMINE_COMMON_MAGIC, 0x9d5827dcd881dd75, 0xa3148604f6fab11b}, 0, NULL};
+__attribute__((used, section(".limine_requests"))) static volatile limine_request_t limine_rsdp_request = {
+    {LIMINE_COMMON_MAGIC, 0xc5e77b6b397e7b43, 0x27637845accdcf3c}, 0, NULL};
+__attribute__((used, section(".limine_requests"))) static volatile limine_request_t limine_module_request = {
+    {LIMINE_COMMON_MAGIC, 0x3e7e279702be32af, 0xca1c4f3bd1280cee}, 0, NULL};
+
+extern int orion_boot_init(const struct orion_boot_info *boot_info);
+
+static boot_info_t g_boot;
+static bool g_boot_ready = false;
+static uint8_t g_rsdp_copy[ACPI_RSDP_MAX] __attribute__((aligned(16)));
+
+// ========================================
+// COMMON HELPERS
+// ========================================
+
+static void boot_copy_string(char *dst, size_t size, const char *src, size_t max)
+{
+    size_t i = 0;
+    for (; src && i + 1 < size && i < max && src[i]; i++)
+    {
+        dst[i] = src[i];
+    }
+    dst[i] = '\0';
+}
+
+static void boot_add_region(uint64_t base, uint64_t length, uint32_t type)
+{
+    if (length == 0)
+    {
+        return;
+    }
+    if (g_boot.memory_count == BOOT_MAX_MEMORY_REGIONS)
+    {
+        kwarn("Boot: memory map truncated at %u entries", BOOT_MAX_MEMORY_REGIONS);
+        return;
+    }
+    boot_memory_region_t *region = &g_boot.memory[g_boot.memory_count++];
+    region->base = base;
+    region->length = length;
+    region->type = type;
+    region->reserved = 0;
+}
+
+static void boot_add_module(uint64_t base, uint64_t size, const char *name, size_t name_max)
+{
+    if (g_boot.module_count == BOOT_MAX_MODULES)
+    {
+        kwarn("Boot: module list truncated at %u modules", BOOT_MAX_MODULES);
+        return;
+    }
+    boot_module_t *module = &g_boot.modules[g_boot.module_count++];
+    module->base = base;
+    module->size = size;
+    boot_copy_string(module->name, sizeof(module->name), name, name_max);
+}
+
+// Sort the map by base, merge adjacent or overlapping entries of the same
+// type and compute the totals. Overlaps between types are kept: the PMM
+// reserves every non-available entry after freeing the available ones
+static void boot_finish_memory_map(void)
+{
+    for (uint32_t i = 1; i < g_boot.memory_count; i++)
+    {
+        boot_memory_region_t region = g_boot.memory[i];
+        uint32_t j = i;
+        for (; j > 0 && g_boot.memory[j - 1].base > region.base; j--)
+        {
+            g_boot.memory[j] = g_boot.memory[j - 1];
+        }
+        g_boot.memory[j] = region;
+    }
+
+    uint32_t count = 0;
+    for (uint32_t i = 0; i < g_boot.memory_count; i++)
+    {
+        boot_memory_region_t *last = count ? &g_boot.memory[count - 1] : NULL;
+        boot_memory_region_t *region = &g_boot.memory[i];
+        if (last && last->type == region->type && region->base <= last->base + last->length)
+        {
+            uint64_t end = region->base + region->length;
+            if (end > last->base + last->length)
+            {
+                last->length = end - last->base;
+            }
+            continue;
+        }
+        g_boot.memory[count++] = *region;
+    }
+    g_boot.memory_count = count;
+
+    g_boot.usable_memory = 0;
+    g_boot.memory_top = 0;
+    for (uint32_t i = 0; i < count; i++)
+    {
+        const boot_memory_region_t *region = &g_boot.memory[i];
+        if (region->type == ORION_MEMORY_AVAILABLE)
+        {
+            g_boot.usable_memory += region->length;
+            if (region->base + region->length > g_boot.memory_top)
+            {
+                g_boot.memory_top = region->base + region->length;
+            }
+        }
+    }
+}
+
+// ========================================
+// ORION BOOT PROTOCOL
+// ========================================
+
+static int boot_parse_orion(const struct orion_boot_info *info)
+{
+    int result = orion_boot_init(info);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    const uint8_t *data = (const uint8_t *)info + sizeof(struct orion_boot_info);
+    uint32_t data_size = info->total_size - sizeof(struct orion_boot_info);
+    uint32_t offset = 0;
+
+    // orion_boot_init validated every tag size against the data size
+    while (offset < data_size)
+    {
+        const struct orion_info_tag *tag = (const struct orion_info_tag *)(data + offset);
+        if (tag->type == ORION_INFO_END)
+        {
+            break;
+        }
+
+        switch (tag->type)
+        {
+        case ORION_INFO_MEMORY:
+        {
+            const struct orion_memory_info *memory = (const struct orion_memory_info *)tag;
+            const struct orion_memory_entry *entries = (const struct orion_memory_entry *)(memory + 1);
+            uint32_t room = (tag->size - sizeof(*memory)) / sizeof(*entries);
+            for (uint32_t i = 0; i < memory->memory_map_entries && i < room; i++)
+            {
+                boot_add_region(entries[i].base_addr, entries[i].length, entries[i].type);
+            }
+            break;
+        }
+        case ORION_INFO_MODULES:
+        {
+            const struct orion_modules_info *modules = (const struct orion_modules_info *)tag;
+            const struct orion_module_entry *entries = (const struct orion_module_entry *)(modules + 1);
+            uint32_t room = (tag->size - sizeof(*modules)) / sizeof(*entries);
+            for (uint32_t i = 0; i < modules->module_count && i < room; i++)
+            {
+                boot_add_module(entries[i].base, entries[i].size, entries[i].name, ORION_MODULE_NAME_MAX);
+            }
+            break;
+        }
+        case ORION_INFO_BOOTLOADER:
+        {
+            const struct orion_bootloader_info *loader = (const struct orion_bootloader_info *)tag;
+            boot_copy_string(g_boot.loader_name, sizeof(g_boot.loader_name), loader->name, sizeof(loader->name));
+            break;
+        }
+        case ORION_INFO_FRAMEBUFFER:
+        {
+            const struct orion_framebuffer_info *fb = (const struct orion_framebuffer_info *)tag;
+            if (tag->size >= sizeof(*fb) && fb->address)
+            {
+                g_boot.has_framebuffer = true;
+                g_boot.framebuffer = (boot_framebuffer_t){
+                    .address = fb->address,
+                    .width = fb->width,
+                    .height = fb->height,
+                    .pitch = fb->pitch,
+                    .bpp = fb->bpp,
+                    .red_size = fb->red_size,
+                    .red_shift = fb->red_shift,
+                    .green_size = fb->green_size,
+                    .green_shift = fb->green_shift,
+                    .blue_size = fb->blue_size,
+                    .blue_shift = fb->blue_shift,
+                };
+            }
+            break;
+        }
+        case ORION_INFO_ACPI:
+            if (tag->size >= sizeof(struct orion_acpi_info))
+            {
+                g_boot.acpi_rsdp = ((const struct orion_acpi_info *)tag)->rsdp;
+            }
+            break;
+        case ORION_INFO_EFI:
+            if (tag->size >= sizeof(struct orion_efi_info))
+            {
+                g_boot.efi_system_table = ((const struct orion_efi_info *)tag)->system_table;
+            }
+            break;
+        default:
+            break;
+        }
+
+        offset += tag->size;
+    }
+
+    g_boot.protocol = BOOT_PROTOCOL_ORION;
+    return OR_OK;
+}
+
+// ========================================
+// MULTIBOOT2
+// ========================================
+
+static uint32_t mb2_u32(const uint8_t *p)
+{
+    uint32_t value;
+    memcpy(&value, p, sizeof(value));
+    return value;
+}
+
+static uint64_t mb2_u64(const uint8_t *p)
+{
+    uint64_t value;
+    memcpy(&value, p, sizeof(value));
+    return value;
+}
+
+static int boot_parse_multiboot2(const uint8_t *mbi)
+{
+    if (!mbi)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint32_t total_size = mb2_u32(mbi);
+    uint32_t offset = 8;
+    // Tags are 8-byte aligned and end with a tag of type 0
+    while (offset + 8 <= total_size)
+    {
+        const uint8_t *tag = mbi + offset;
+        uint32_t type = mb2_u32(tag);
+        uint32_t size = mb2_u32(tag + 4);
+        if (type == MB2_TAG_END || size < 8 || offset + size > total_size)
+        {
+            break;
+        }
+
+        switch (type)
+        {
+        case MB2_TAG_CMDLINE:
+            boot_copy_string(g_boot.command_line, sizeof(g_boot.command_line), (const char *)tag + 8, size - 8);
+            break;
+        case MB2_TAG_LOADER_NAME:
+            boot_copy_string(g_boot.loader_name, sizeof(g_boot.loader_name), (const char *)tag + 8, size - 8);
+            break;
+        case MB2_TAG_MODULE:
+            if (size >= 16)
+            {
+                uint32_t start = mb2_u32(tag + 8);
+                uint32_t end = mb2_u32(tag + 12);
+                boot_add_module(start, end > start ? end - start : 0, (const char *)tag + 16, size - 16);
+            }
+            break;
+        case MB2_TAG_MMAP:
+        {
+            uint32_t entry_size = size >= 16 ? mb2_u32(tag + 8) : 0;
+            if (entry_size < 24)
+            {
+                break;
+            }
+            for (uint32_t at = 16; at + entry_size <= size; at += entry_size)
+            {
+                // Multiboot2 types 1-5 are the ORION_MEMORY_* values
+                uint32_t kind = mb2_u32(tag + at + 16);
+                boot_add_region(mb2_u64(tag + at), mb2_u64(tag + at + 8),
+                                kind >= ORION_MEMORY_AVAILABLE && kind <= ORION_MEMORY_BAD ? kind
+                                                                                          : ORION_MEMORY_RESERVED);
+            }
+            break;
+        }
+        case MB2_TAG_FRAMEBUFFER:
+            // Only direct RGB colour can be drawn to without a palette
+            if (size >= 38 && tag[29] == MB2_FRAMEBUFFER_RGB)
+            {
+                g_boot.has_framebuffer = true;
+                g_boot.framebuffer = (boot_framebuffer_t){
+                    .address = mb2_u64(tag + 8),
+                    .pitch = mb2_u32(tag + 16),
+                    .width = mb2_u32(tag + 20),
+                    .height = mb2_u32(tag + 24),
+                    .bpp = tag[28],
+                    .red_shift = tag[32],
+                    .red_size = tag[33],
+                    .green_shift = tag[34],
+                    .green_size = tag[35],
+                    .blue_shift = tag[36],
+                    .blue_size = tag[37],
+                };
+            }
+            break;
+        case MB2_TAG_ACPI_OLD:
+        case MB2_TAG_ACPI_NEW:
+        {
+            // The loader hands over a copy of the RSDP rather than its
+            // address; keep the copy in the kernel image. A new-style copy
+            // wins over an old-style one
+            uint32_t length = size - 8 < ACPI_RSDP_MAX ? size - 8 : ACPI_RSDP_MAX;
+            if (length >= 20 && (type == MB2_TAG_ACPI_NEW || !g_boot.acpi_rsdp))
+            {
+                memset(g_rsdp_copy, 0, sizeof(g_rsdp_copy));
+                memcpy(g_rsdp_copy, tag + 8, length);
+                g_boot.acpi_rsdp = (uint64_t)(uintptr_t)g_rsdp_copy;
+            }
+            break;
+        }
+        default:
+            break;
+        }
+
+        offset += (size + 7) & ~7u;
+    }
+
+    g_boot.protocol = BOOT_PROTOCOL_MULTIBOOT2;
+    return OR_OK;
+}
+
+// ========================================
+// LIMINE
+// ========================================
+
+static uint32_t limine_memory_type(uint64_t type)
+{
+    switch (type)
+    {
+    case LIMINE_MEMMAP_USABLE:
+        return ORION_MEMORY_AVAILABLE;
+    case LIMINE_MEMMAP_ACPI_RECLAIMABLE:
+        return ORION_MEMORY_ACPI_RECLAIMABLE;
+    case LIMINE_MEMMAP_ACPI_NVS:
+        return ORION_MEMORY_ACPI_NVS;
+    case LIMINE_MEMMAP_BAD_MEMORY:
+        return ORION_MEMORY_BAD;
+    case LIMINE_MEMMAP_BOOTLOADER_RECLAIMABLE:
+        return ORION_MEMORY_BOOTLOADER_RECLAIMABLE;
+    case LIMINE_MEMMAP_KERNEL_AND_MODULES:
+        return ORION_MEMORY_KERNEL;
+    case LIMINE_MEMMAP_FRAMEBUFFER:
+        return ORION_MEMORY_FRAMEBUFFER;
+    default:
+        return ORION_MEMORY_RESERVED;
+    }
+}
+
+static int boot_parse_limine(void)
+{
+    const limine_memmap_response_t *memmap = limine_memmap_request.response;
+    const limine_hhdm_response_t *hhdm = limine_hhdm_request.response;
+    const limine_bootloader_info_response_t *loader = limine_bootloader_request.response;
+    const limine_framebuffer_response_t *fbs = limine_framebuffer_request.response;
+    const limine_rsdp_response_t *rsdp = limine_rsdp_request.response;
+    const limine_module_response_t *modules = limine_module_request.response;
+
+    // Addresses Limine hands over point into the higher-half direct map
+    uint64_t offset = hhdm ? hhdm->offset : 0;
+#define LIMINE_PHYS(addr) ((uint64_t)(uintptr_t)(addr) >= offset ? (uint64_t)(uintptr_t)(addr) - offset : (uint64_t)(uintptr_t)(addr))
+
+    for (uint64_t i = 0; i < memmap->entry_count; i++)
+    {
+        const limine_memmap_entry_t *entry = memmap->entries[i];
+        boot_add_region(entry->base, entry->length, limine_memory_type(entry->type));
+    }
+
+    if (loader)
+    {
+        boot_copy_string(g_boot.loader_name, sizeof(g_boot.loader_name), loader->name, BOOT_LOADER_NAME_MAX);
+    }
+
+    if (fbs && fbs->framebuffer_count > 0)
+    {
+        const limine_framebuffer_t *fb = fbs->framebuffers[0];
+        if (fb->memory_model == LIMINE_FRAMEBUFFER_RGB)
+        {
+            g_boot.has_framebuffer = true;
+            g_boot.framebuffer = (boot_framebuffer_t){
+                .address = LIMINE_PHYS(fb->address),
+                .width = (uint32_t)fb->width,
+                .height = (uint32_t)fb->height,
+                .pitch = (uint32_t)fb->pitch,
+                .bpp = fb->bpp,
+                .red_size = fb->red_mask_size,
+                .red_shift = fb->red_mask_shift,
+                .green_size = fb->green_mask_size,
+                .green_shift = fb->green_mask_shift,
+                .blue_size = fb->blue_mask_size,
+                .blue_shift = fb->blue_mask_shift,
+            };
+        }
+    }
+
+    if (rsdp && rsdp->address)
+    {
+        // Physical from base revision 3 on, in the direct map before
+        bool physical = limine_base_revision[2] == 0;
+        g_boot.acpi_rsdp = physical ? rsdp->address : LIMINE_PHYS(rsdp->address);
+    }
+
+    for (uint64_t i = 0; modules && i < modules->module_count; i++)
+    {
+        const limine_file_t *file = modules->modules[i];
+        boot_add_module(LIMINE_PHYS(file->address), file->size, file->path, ORION_MODULE_NAME_MAX);
+    }
+#undef LIMINE_PHYS
+
+    g_boot.protocol = BOOT_PROTOCOL_LIMINE;
+    return OR_OK;
+}
+
+// ========================================
+// PUBLIC INTERFACE
+// ========================================
+
+int boot_info_init(const struct orion_boot_info *orion)
+{
+    int result;
+
+    memset(&g_boot, 0, sizeof(g_boot));
+    g_boot_ready = false;
+
+    if (&boot_protocol_magic && boot_protocol_magic == MULTIBOOT2_BOOTLOADER_MAGIC)
+    {
+        result = boot_parse_multiboot2((const uint8_t *)(uintptr_t)boot_protocol_data);
+    }
+    else if (limine_memmap_request.response)
+    {
+        result = boot_parse_limine();
+    }
+    else
+    {
+        result = boot_parse_orion(orion);
+    }
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    boot_finish_memory_map();
+    if (g_boot.usable_memory == 0)
+    {
+        kerror("Boot: the loader reported no usable memory");
+        return -OR_EINVAL;
+    }
+
+    if (__kernel_start && __kernel_end)
+    {
+        g_boot.kernel_base = (uint64_t)(uintptr_t)__kernel_start;
+        g_boot.kernel_end = (uint64_t)(uintptr_t)__kernel_end;
+    }
+
+    g_boot_ready = true;
+    return OR_OK;
+}
+
+const boot_info_t *boot_info_get(void)
+{
+    return g_boot_ready ? &g_boot : NULL;
+}
+
+const char *boot_memory_type_name(uint32_t type)
+{
+    switch (type)
+    {
+    case ORION_MEMORY_AVAILABLE:
+        return "available";
+    case ORION_MEMORY_RESERVED:
+        return "reserved";
+    case ORION_MEMORY_ACPI_RECLAIMABLE:
+        return "ACPI reclaimable";
+    case ORION_MEMORY_ACPI_NVS:
+        return "ACPI NVS";
+    case ORION_MEMORY_BAD:
+        return "bad";
+    case ORION_MEMORY_BOOTLOADER_RECLAIMABLE:
+        return "loader reclaimable";
+    case ORION_MEMORY_KERNEL:
+        return "kernel and modules";
+    case ORION_MEMORY_FRAMEBUFFER:
+        return "framebuffer";
+    default:
+        return "unknown";
+    }
+}
+
+void boot_info_print(void)
+{
+    static const char *const protocols[] = {"?", "Orion", "Multiboot2", "Limine"};
+
+    if (!g_boot_ready)
+    {
+        return;
+    }
+
+    kinfo("Boot: %s protocol%s%s", protocols[g_boot.protocol], g_boot.loader_name[0] ? ", loader " : "",
+          g_boot.loader_name);
+    if (g_boot.command_line[0])
+    {
+        kinfo("Boot: command line \"%s\"", g_boot.command_line);
+    }
+    for (uint32_t i = 0; i < g_boot.memory_count; i++)
+    {
+        const boot_memory_region_t *region = &g_boot.memory[i];
+        kinfo("  [0x%016llx-0x%016llx] %s", (unsigned long long)region->base,
+              (unsigned long long)(region->base + region->length - 1), boot_memory_type_name(region->type));
+    }
+    kinfo("Boot: %llu MB usable, top of memory 0x%llx", (unsigned long long)(g_boot.usable_memory >> 20),
+          (unsigned long long)g_boot.memory_top);
+    if (g_boot.acpi_rsdp)
+    {
+        kinfo("Boot: ACPI RSDP at 0x%llx", (unsigned long long)g_boot.acpi_rsdp);
+    }
+    if (g_boot.has_framebuffer)
+    {
+        kinfo("Boot: framebuffer %ux%u, %u bpp, pitch %u at 0x%llx", g_boot.framebuffer.width,
+              g_boot.framebuffer.height, g_boot.framebuffer.bpp, g_boot.framebuffer.pitch,
+              (unsigned long long)g_boot.framebuffer.address);
+    }
+    for (uint32_t i = 0; i < g_boot.module_count; i++)
+    {
+        kinfo("Boot: module %s, %llu bytes at 0x%llx", g_boot.modules[i].name,
+              (unsigned long long)g_boot.modules[i].size, (unsigned long long)g_boot.modules[i].base);
+    }
+}
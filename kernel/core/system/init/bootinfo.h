@@ -0,0 +1,123 @@
+/*
+ * Orion Operating System - Boot Information
+ *
+ * What the kernel knows about the machine when it is entered, in one
+ * typed structure whatever loaded it: the Orion UEFI loader (tagged
+ * Orion Boot Protocol data), a Multiboot2 loader such as GRUB, or Limine
+ * (requests answered in the kernel image). The memory map, ACPI root
+ * pointer, framebuffer and module list are copied out of the loader's
+ * data before memory management starts, so nothing here points into
+ * memory the PMM may hand out. Physical memory management, ACPI table
+ * discovery and the fallback framebuffer driver take the machine layout
+ * from here instead of assuming one.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_BOOTINFO_H
+#define ORION_BOOTINFO_H
+
+#include <orion/types.h>
+#include "orion-boot-protocol.h"
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define BOOT_MAX_MEMORY_REGIONS 128
+#define BOOT_MAX_MODULES 16
+#define BOOT_COMMAND_LINE_MAX 256
+#define BOOT_LOADER_NAME_MAX 32
+
+// Low memory kept away from the PMM: real-mode structures, the EBDA and
+// the AP startup trampoline
+#define BOOT_LOW_MEMORY_RESERVED 0x100000
+
+    typedef enum boot_protocol
+    {
+        BOOT_PROTOCOL_ORION = 1,
+        BOOT_PROTOCOL_MULTIBOOT2 = 2,
+        BOOT_PROTOCOL_LIMINE = 3,
+    } boot_protocol_t;
+
+    // Memory map entry, type being one of ORION_MEMORY_*
+    typedef struct boot_memory_region
+    {
+        uint64_t base;
+        uint64_t length;
+        uint32_t type;
+        uint32_t reserved;
+    } boot_memory_region_t;
+
+    // Linear framebuffer left by the loader, also the SYS_BOOT_FRAMEBUFFER ABI
+    typedef struct boot_framebuffer
+    {
+        uint64_t address; // Physical address of the first pixel
+        uint32_t width;
+        uint32_t height;
+        uint32_t pitch; // Bytes per line
+        uint16_t bpp;
+        uint8_t red_size;
+        uint8_t red_shift;
+        uint8_t green_size;
+        uint8_t green_shift;
+        uint8_t blue_size;
+        uint8_t blue_shift;
+    } boot_framebuffer_t;
+
+    typedef struct boot_module
+    {
+        uint64_t base; // Physical load address
+        uint64_t size;
+        char name[ORION_MODULE_NAME_MAX];
+    } boot_module_t;
+
+    typedef struct boot_info
+    {
+        boot_protocol_t protocol;
+        char loader_name[BOOT_LOADER_NAME_MAX];
+        char command_line[BOOT_COMMAND_LINE_MAX];
+
+        // Sorted by base, adjacent entries of the same type merged
+        boot_memory_region_t memory[BOOT_MAX_MEMORY_REGIONS];
+        uint32_t memory_count;
+        uint64_t usable_memory; // Bytes in ORION_MEMORY_AVAILABLE regions
+        uint64_t memory_top;    // End of the highest available region
+
+        uint64_t acpi_rsdp; // Physical address of the RSDP, 0 if unknown
+        uint64_t efi_system_table;
+
+        bool has_framebuffer;
+        boot_framebuffer_t framebuffer;
+
+        boot_module_t modules[BOOT_MAX_MODULES];
+        uint32_t module_count;
+
+        // Physical bounds of the kernel image
+        uint64_t kernel_base;
+        uint64_t kernel_end;
+    } boot_info_t;
+
+    // Build the boot information from whichever protocol entered the
+    // kernel; `orion` is what the Orion loader passed, ignored when the
+    // kernel was started through Multiboot2 or Limine. Fails without a
+    // usable memory map
+    int boot_info_init(const struct orion_boot_info *orion);
+
+    // Boot information, NULL before boot_info_init succeeded
+    const boot_info_t *boot_info_get(void);
+
+    void boot_info_print(void);
+
+    const char *boot_memory_type_name(uint32_t type);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_BOOTINFO_H
@@ -279,6 +279,7 @@ static int start_system_daemons(void);
 static int start_window_manager(void);
 static int start_desktop_environment(void);
 static int start_user_applications(void);
+static int confirm_boot(void);
 static void process_user_space_requests(void);
 static void handle_system_events(void);
 static void manage_user_space_processes(void);
@@ -353,6 +354,9 @@ void init_process_entry(void)
 
     klog_info(KLOG_CAT_PROCESS, "Init: User interface started");
 
+    // The boot is complete: a system slot or configuration on trial is kept
+    confirm_boot();
+
     // Main init loop
     klog_info(KLOG_CAT_PROCESS, "Init: Entering main loop");
 
@@ -605,6 +609,18 @@ static int start_system_daemons(void)
         klog_info(KLOG_CAT_PROCESS, "Device daemon started (PID: %d)", result);
     }
 
+    // Start update daemon
+    result = start_daemon("update", "/usr/sbin/updated");
+    if (result >= 0)
+    {
+        user_daemons[daemon_count].pid = result;
+        strncpy(user_daemons[daemon_count].name, "update", sizeof(user_daemons[daemon_count].name) - 1);
+        user_daemons[daemon_count].running = true;
+        user_daemons[daemon_count].start_time = arch_get_timestamp();
+        daemon_count++;
+        klog_info(KLOG_CAT_PROCESS, "Update daemon started (PID: %d)", result);
+    }
+
     klog_info(KLOG_CAT_PROCESS, "System daemons started successfully");
     return 0;
 }
@@ -720,6 +736,28 @@ static int start_user_applications(void)
     return 0;
 }
 
+/**
+ * Confirm the boot
+ *
+ * Runs the one-shot confirmation once everything started. The update
+ * server keeps a newly installed system slot only if this happens within
+ * its deadline; a boot that hangs before never gets here and the
+ * bootloader returns to the previous slot.
+ */
+static int confirm_boot(void)
+{
+    klog_info(KLOG_CAT_PROCESS, "Confirming the boot...");
+
+    int result = start_daemon("boot-confirm", "/usr/bin/orion-update confirm");
+    if (result < 0)
+    {
+        klog_err(KLOG_CAT_PROCESS, "Failed to confirm the boot: %d", result);
+        return result;
+    }
+
+    return 0;
+}
+
 /**
  * Process user space requests
  */
@@ -13,8 +13,20 @@
 
 #include <orion/kernel.h>
 #include "orion-boot-protocol.h"
+#include <orion/bootinfo.h>
+#include <orion/earlycon.h>
 #include <orion/security.h>
+#include <orion/measured_boot.h>
+#include <orion/wallclock.h>
 #include <orion/mm.h>
+#include <orion/aslr.h>
+#include <orion/oom.h>
+#include <orion/ksm.h>
+#include <orion/namespace.h>
+#include <orion/mac.h>
+#include <orion/irq.h>
+#include <orion/smp.h>
+#include <orion/scheduler.h>
 #include <orion/types.h>
 #include <orion/constants.h>
 #include <orion/structures.h>
@@ -43,9 +55,10 @@ static void kernel_idle_loop(void);
  *
  * This function is called by the architecture-specific boot code after
  * basic CPU and memory setup is complete. It receives boot information
- * from the Orion bootloader via the Orion Boot Protocol.
+ * from the Orion bootloader via the Orion Boot Protocol, or finds it
+ * through Multiboot2 or Limine when one of those started the kernel.
  *
- * @param boot_info Pointer to boot information from bootloader
+ * @param boot_info Pointer to Orion boot information, unused otherwise
  */
 void kernel_main(struct orion_boot_info *boot_info)
 {
@@ -55,16 +68,28 @@ void kernel_main(struct orion_boot_info *boot_info)
     kprintf("%s", orion_banner);
     klog_info(KLOG_CAT_KERNEL, "Starting Orion OS...");
 
-    // Initialize boot information processing
-    result = orion_boot_init(boot_info);
+    // Collect boot information from whichever protocol started the kernel
+    result = boot_info_init(boot_info);
     if (result != 0)
     {
         kprintf("PANIC: Failed to initialize boot information: %d\n", result);
         kernel_panic("Boot initialization failed");
     }
 
-    // Debug print boot information
-    orion_boot_debug_print(boot_info);
+    // Show the console on the loader's framebuffer until the console
+    // server takes the display over; what was printed so far is replayed
+    const boot_info_t *boot = boot_info_get();
+    if (boot->has_framebuffer)
+    {
+        result = earlycon_attach(&boot->framebuffer);
+        if (result != 0)
+        {
+            klog_warning(KLOG_CAT_KERNEL, "Early console unavailable on this framebuffer: %d", result);
+        }
+    }
+
+    // Print the machine layout handed over by the loader
+    boot_info_print();
 
     // Early kernel initialization
     klog_info(KLOG_CAT_KERNEL, "Early initialization...");
@@ -101,14 +126,21 @@ static int kernel_early_init(void)
     // Initialize memory management
     klog_info(KLOG_CAT_KERNEL, "Initializing memory management...");
     mm_init();
+    oom_init();
+    ksm_init();
 
     // Initialize interrupt handling
     klog_info(KLOG_CAT_KERNEL, "Initializing interrupt handling...");
+    irq_init();
     arch_interrupt_init();
 
+    // Per-CPU areas, now that the heap is up and the CPUs are known
+    smp_init();
+
     // Initialize timer subsystem
     klog_info(KLOG_CAT_KERNEL, "Initializing timer subsystem...");
     arch_timer_init();
+    wallclock_init(); // Seed the UTC clock from the RTC
 
     klog_info(KLOG_CAT_KERNEL, "Early initialization complete");
     return 0;
@@ -126,6 +158,12 @@ static int kernel_subsystem_init(void)
     // Initialize process scheduler
     klog_info(KLOG_CAT_KERNEL, "Initializing process scheduler...");
     scheduler_init();
+    ns_init();
+    mac_init();
+
+    // Start the other CPUs; each one joins the scheduler
+    klog_info(KLOG_CAT_KERNEL, "Starting secondary CPUs...");
+    smp_boot();
 
     // Initialize IPC subsystem
     klog_info(KLOG_CAT_KERNEL, "Initializing IPC subsystem...");
@@ -133,8 +171,10 @@ static int kernel_subsystem_init(void)
 
     // Initialize security subsystem (capabilities, hardening, etc.)
     klog_info(KLOG_CAT_KERNEL, "Initializing security subsystem...");
-    capabilities_init(); // Initialize capability system
-    security_init();     // Initialize hardware security features
+    capabilities_init();  // Initialize capability system
+    security_init();      // Initialize hardware security features
+    measured_boot_init(); // Measure the kernel image into the event log
+    aslr_init();          // Randomized layouts and guard pages, seeded by the entropy pool
     klog_info(KLOG_CAT_SECURITY, "Security subsystem initialized successfully");
 
     // Initialize system call interface
@@ -212,14 +252,8 @@ static void kernel_idle_loop(void)
     kprintf("Type 'help' for available commands\n");
     kprintf("orion$ ");
 
-    while (1)
-    {
-        // Wait for interrupts
-        arch_halt();
-
-        // Handle any pending work
-        sched_yield();
-    }
+    // Run threads as they become ready, sleeping in between
+    scheduler_idle_loop();
 }
 
 /**
@@ -0,0 +1,415 @@
+/*
+ * Orion Operating System - Early Framebuffer Console
+ *
+ * Draws kernel console output on the loader's framebuffer until the
+ * console server takes the display over, and keeps a scrollback ring of
+ * everything written to the console since the first character. Works
+ * from static storage only: it runs before memory management and must
+ * keep working when the kernel panics.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/virtio_mmio.h>
+#include "earlycon.h"
+
+#define EARLYCON_SCROLLBACK_MASK (EARLYCON_SCROLLBACK_SIZE - 1)
+#define EARLYCON_TAB_WIDTH 8
+
+// Light grey on black, as 8-bit channels
+#define EARLYCON_FOREGROUND 0xAAAAAAU
+#define EARLYCON_BACKGROUND 0x000000U
+
+// Printable ASCII, one byte per glyph row, bit 0 the leftmost pixel
+static const uint8_t g_font[0x7F - 0x20][EARLYCON_FONT_HEIGHT] = {
+    {0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00}, // 0x20 space
+    {0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00}, // 0x21 !
+    {0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00}, // 0x22 "
+    {0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00}, // 0x23 #
+    {0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00}, // 0x24 $
+    {0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00}, // 0x25 %
+    {0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00}, // 0x26 &
+    {0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00}, // 0x27 quote
+    {0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00}, // 0x28 (
+    {0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00}, // 0x29 )
+    {0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00}, // 0x2A *
+    {0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00}, // 0x2B +
+    {0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06}, // 0x2C ,
+    {0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00}, // 0x2D -
+    {0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00}, // 0x2E .
+    {0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00}, // 0x2F /
+    {0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00}, // 0x30 0
+    {0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00}, // 0x31 1
+    {0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00}, // 0x32 2
+    {0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00}, // 0x33 3
+    {0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00}, // 0x34 4
+    {0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00}, // 0x35 5
+    {0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00}, // 0x36 6
+    {0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00}, // 0x37 7
+    {0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00}, // 0x38 8
+    {0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00}, // 0x39 9
+    {0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00}, // 0x3A :
+    {0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06}, // 0x3B ;
+    {0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00}, // 0x3C <
+    {0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00}, // 0x3D =
+    {0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00}, // 0x3E >
+    {0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00}, // 0x3F ?
+    {0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00}, // 0x40 @
+    {0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00}, // 0x41 A
+    {0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00}, // 0x42 B
+    {0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00}, // 0x43 C
+    {0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00}, // 0x44 D
+    {0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00}, // 0x45 E
+    {0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00}, // 0x46 F
+    {0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00}, // 0x47 G
+    {0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00}, // 0x48 H
+    {0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00}, // 0x49 I
+    {0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00}, // 0x4A J
+    {0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00}, // 0x4B K
+    {0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00}, // 0x4C L
+    {0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00}, // 0x4D M
+    {0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00}, // 0x4E N
+    {0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00}, // 0x4F O
+    {0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00}, // 0x50 P
+    {0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00}, // 0x51 Q
+    {0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00}, // 0x52 R
+    {0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00}, // 0x53 S
+    {0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00}, // 0x54 T
+    {0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00}, // 0x55 U
+    {0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00}, // 0x56 V
+    {0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00}, // 0x57 W
+    {0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00}, // 0x58 X
+    {0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00}, // 0x59 Y
+    {0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00}, // 0x5A Z
+    {0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00}, // 0x5B [
+    {0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00}, // 0x5C backslash
+    {0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00}, // 0x5D ]
+    {0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00}, // 0x5E ^
+    {0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF}, // 0x5F _
+    {0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00}, // 0x60 `
+    {0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00}, // 0x61 a
+    {0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00}, // 0x62 b
+    {0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00}, // 0x63 c
+    {0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00}, // 0x64 d
+    {0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00}, // 0x65 e
+    {0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00}, // 0x66 f
+    {0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F}, // 0x67 g
+    {0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00}, // 0x68 h
+    {0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00}, // 0x69 i
+    {0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E}, // 0x6A j
+    {0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00}, // 0x6B k
+    {0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00}, // 0x6C l
+    {0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00}, // 0x6D m
+    {0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00}, // 0x6E n
+    {0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00}, // 0x6F o
+    {0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F}, // 0x70 p
+    {0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78}, // 0x71 q
+    {0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00}, // 0x72 r
+    {0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00}, // 0x73 s
+    {0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00}, // 0x74 t
+    {0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00}, // 0x75 u
+    {0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00}, // 0x76 v
+    {0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00}, // 0x77 w
+    {0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00}, // 0x78 x
+    {0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F}, // 0x79 y
+    {0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00}, // 0x7A z
+    {0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00}, // 0x7B {
+    {0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00}, // 0x7C |
+    {0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00}, // 0x7D }
+    {0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00}, // 0x7E ~
+};
+
+static char g_scrollback[EARLYCON_SCROLLBACK_SIZE];
+static uint64_t g_written;
+static spinlock_t g_earlycon_lock = SPINLOCK_INIT;
+
+// Screen state, valid while g_attached
+static bool g_attached;
+static bool g_released;
+static boot_framebuffer_t g_fb;
+static uint8_t *g_pixels;
+static uint32_t g_bytes_per_pixel;
+static uint32_t g_scale;
+static uint32_t g_columns;
+static uint32_t g_rows;
+static uint32_t g_column;
+static uint32_t g_row;
+static uint32_t g_foreground;
+static uint32_t g_background;
+
+// ========================================
+// DRAWING
+// ========================================
+
+// Pack an 8-bit-per-channel colour into the framebuffer's pixel format
+static uint32_t pack_colour(uint32_t rgb)
+{
+    uint32_t red = (rgb >> 16) & 0xFF;
+    uint32_t green = (rgb >> 8) & 0xFF;
+    uint32_t blue = rgb & 0xFF;
+    return ((red >> (8 - g_fb.red_size)) << g_fb.red_shift) | ((green >> (8 - g_fb.green_size)) << g_fb.green_shift) |
+           ((blue >> (8 - g_fb.blue_size)) << g_fb.blue_shift);
+}
+
+static void put_pixel(uint8_t *at, uint32_t value)
+{
+    switch (g_bytes_per_pixel)
+    {
+    case 4:
+        *(volatile uint32_t *)at = value;
+        break;
+    case 3:
+        at[0] = (uint8_t)value;
+        at[1] = (uint8_t)(value >> 8);
+        at[2] = (uint8_t)(value >> 16);
+        break;
+    default:
+        *(volatile uint16_t *)at = (uint16_t)value;
+        break;
+    }
+}
+
+static uint32_t cell_width(void)
+{
+    return EARLYCON_FONT_WIDTH * g_scale;
+}
+
+static uint32_t cell_height(void)
+{
+    return EARLYCON_FONT_HEIGHT * g_scale;
+}
+
+static void fill_rows(uint32_t y, uint32_t height, uint32_t value)
+{
+    for (uint32_t line = y; line < y + height; line++)
+    {
+        uint8_t *row = g_pixels + (uint64_t)line * g_fb.pitch;
+        for (uint32_t x = 0; x < g_fb.width; x++)
+        {
+            put_pixel(row + x * g_bytes_per_pixel, value);
+        }
+    }
+}
+
+static void draw_glyph(uint32_t column, uint32_t row, char c)
+{
+    const uint8_t *glyph = g_font[(unsigned char)c - 0x20];
+    uint32_t x0 = column * cell_width();
+    uint32_t y0 = row * cell_height();
+    for (uint32_t y = 0; y < cell_height(); y++)
+    {
+        uint8_t bits = glyph[y / g_scale];
+        uint8_t *line = g_pixels + (uint64_t)(y0 + y) * g_fb.pitch + (uint64_t)x0 * g_bytes_per_pixel;
+        for (uint32_t x = 0; x < cell_width(); x++)
+        {
+            bool set = (bits >> (x / g_scale)) & 1;
+            put_pixel(line + x * g_bytes_per_pixel, set ? g_foreground : g_background);
+        }
+    }
+}
+
+// Move every text row up by one and clear the last
+static void scroll(void)
+{
+    uint64_t row_bytes = (uint64_t)g_fb.pitch * cell_height();
+    memmove(g_pixels, g_pixels + row_bytes, row_bytes * (g_rows - 1));
+    fill_rows((g_rows - 1) * cell_height(), cell_height(), g_background);
+}
+
+static void newline(void)
+{
+    g_column = 0;
+    if (++g_row == g_rows)
+    {
+        scroll();
+        g_row = g_rows - 1;
+    }
+}
+
+static void draw_char(char c)
+{
+    switch (c)
+    {
+    case '\n':
+        newline();
+        return;
+    case '\t':
+        g_column = (g_column + EARLYCON_TAB_WIDTH) & ~(uint32_t)(EARLYCON_TAB_WIDTH - 1);
+        if (g_column >= g_columns)
+        {
+            newline();
+        }
+        return;
+    case '\b':
+        if (g_column > 0)
+        {
+            g_column--;
+        }
+        return;
+    default:
+        break;
+    }
+
+    // Other control characters and anything beyond ASCII show as '?'
+    if ((unsigned char)c < 0x20 || (unsigned char)c > 0x7E)
+    {
+        c = '?';
+    }
+    if (g_column == g_columns)
+    {
+        newline();
+    }
+    draw_glyph(g_column++, g_row, c);
+}
+
+// ========================================
+// SCROLLBACK
+// ========================================
+
+static uint64_t oldest_offset(void)
+{
+    return g_written > EARLYCON_SCROLLBACK_SIZE ? g_written - EARLYCON_SCROLLBACK_SIZE : 0;
+}
+
+static char scrollback_at(uint64_t offset)
+{
+    return g_scrollback[offset & EARLYCON_SCROLLBACK_MASK];
+}
+
+// Offset from which replaying the ring fills the screen once: the start
+// of the line `g_rows` lines back from the end
+static uint64_t replay_start(void)
+{
+    uint64_t oldest = oldest_offset();
+    uint32_t lines = 0;
+    for (uint64_t offset = g_written; offset > oldest; offset--)
+    {
+        if (scrollback_at(offset - 1) == '\n' && ++lines == g_rows)
+        {
+            return offset;
+        }
+    }
+    return oldest;
+}
+
+// ========================================
+// INTERFACE
+// ========================================
+
+void earlycon_putchar(char c)
+{
+    if (c == '\r')
+    {
+        return;
+    }
+
+    spinlock_lock(&g_earlycon_lock);
+    g_scrollback[g_written & EARLYCON_SCROLLBACK_MASK] = c;
+    g_written++;
+    if (g_attached && !g_released)
+    {
+        draw_char(c);
+    }
+    spinlock_unlock(&g_earlycon_lock);
+}
+
+void earlycon_puts(const char *str)
+{
+    while (*str)
+    {
+        earlycon_putchar(*str++);
+    }
+}
+
+int earlycon_attach(const boot_framebuffer_t *framebuffer)
+{
+    if (!framebuffer || !framebuffer->address || (framebuffer->bpp != 16 && framebuffer->bpp != 24 && framebuffer->bpp != 32))
+    {
+        return -OR_EINVAL;
+    }
+    if (framebuffer->red_size > 8 || framebuffer->green_size > 8 || framebuffer->blue_size > 8)
+    {
+        return -OR_EINVAL;
+    }
+    uint32_t scale = framebuffer->width >= EARLYCON_SCALE_WIDTH ? 2 : 1;
+    if (framebuffer->width < EARLYCON_FONT_WIDTH * scale || framebuffer->height < EARLYCON_FONT_HEIGHT * scale)
+    {
+        return -OR_EINVAL;
+    }
+    uint8_t *pixels = (uint8_t *)arch_ioremap(framebuffer->address, (size_t)framebuffer->pitch * framebuffer->height);
+    if (!pixels)
+    {
+        return -OR_ENOMEM;
+    }
+
+    spinlock_lock(&g_earlycon_lock);
+    g_fb = *framebuffer;
+    g_pixels = pixels;
+    g_bytes_per_pixel = framebuffer->bpp / 8;
+    g_scale = scale;
+    g_columns = framebuffer->width / cell_width();
+    g_rows = framebuffer->height / cell_height();
+    g_column = 0;
+    g_row = 0;
+    g_foreground = pack_colour(EARLYCON_FOREGROUND);
+    g_background = pack_colour(EARLYCON_BACKGROUND);
+    fill_rows(0, g_fb.height, g_background);
+
+    // What the console printed before the framebuffer was known
+    for (uint64_t offset = replay_start(); offset < g_written; offset++)
+    {
+        draw_char(scrollback_at(offset));
+    }
+    g_attached = true;
+    g_released = false;
+    spinlock_unlock(&g_earlycon_lock);
+
+    kinfo("earlycon: %ux%u framebuffer, %ux%u text", framebuffer->width, framebuffer->height, g_columns, g_rows);
+    return OR_OK;
+}
+
+size_t earlycon_read(uint64_t offset, char *buffer, size_t size)
+{
+    spinlock_lock(&g_earlycon_lock);
+    uint64_t start = offset > oldest_offset() ? offset : oldest_offset();
+    size_t copied = 0;
+    for (uint64_t at = start; at < g_written && copied < size; at++)
+    {
+        buffer[copied++] = scrollback_at(at);
+    }
+    spinlock_unlock(&g_earlycon_lock);
+    return copied;
+}
+
+void earlycon_status(earlycon_status_t *status)
+{
+    spinlock_lock(&g_earlycon_lock);
+    *status = (earlycon_status_t){
+        .flags = (g_attached ? EARLYCON_ATTACHED : 0) | (g_released ? EARLYCON_RELEASED : 0),
+        .columns = g_attached ? g_columns : 0,
+        .rows = g_attached ? g_rows : 0,
+        .column = g_attached ? g_column : 0,
+        .row = g_attached ? g_row : 0,
+        .cell_width = g_attached ? cell_width() : 0,
+        .cell_height = g_attached ? cell_height() : 0,
+        .scrollback_size = EARLYCON_SCROLLBACK_SIZE,
+        .written = g_written,
+        .oldest = oldest_offset(),
+    };
+    spinlock_unlock(&g_earlycon_lock);
+}
+
+uint64_t earlycon_release(void)
+{
+    spinlock_lock(&g_earlycon_lock);
+    g_released = true;
+    uint64_t written = g_written;
+    spinlock_unlock(&g_earlycon_lock);
+    return written;
+}
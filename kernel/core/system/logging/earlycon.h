@@ -0,0 +1,93 @@
+/*
+ * Orion Operating System - Early Framebuffer Console
+ *
+ * Between the loader handing over and a display driver starting, the
+ * only output is the serial port. The early console draws everything
+ * written to the kernel console straight onto the framebuffer the loader
+ * left behind (boot_info_t.framebuffer), with a built-in 8x8 font and no
+ * allocation, so kernel messages are visible on a machine without a
+ * serial cable.
+ *
+ * Console output is recorded in a scrollback ring from the very first
+ * character, before the framebuffer is known, and replayed when the
+ * console attaches. The console server takes over through
+ * SYS_EARLYCON_CTL: it reads the geometry and cursor, copies the
+ * scrollback, then releases the early console, which stops drawing but
+ * keeps recording, so nothing written during the handoff is lost.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_EARLYCON_H
+#define ORION_EARLYCON_H
+
+#include <orion/types.h>
+#include <orion/bootinfo.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Bytes of console output kept, a power of two
+#define EARLYCON_SCROLLBACK_SIZE (64 * 1024)
+
+// Glyph size before scaling
+#define EARLYCON_FONT_WIDTH 8
+#define EARLYCON_FONT_HEIGHT 8
+
+// Framebuffers at least this wide get glyphs drawn twice the size
+#define EARLYCON_SCALE_WIDTH 1280
+
+// earlycon_status_t.flags
+#define EARLYCON_ATTACHED (1U << 0) // Drawing on the boot framebuffer
+#define EARLYCON_RELEASED (1U << 1) // Handed over, recording only
+
+// SYS_EARLYCON_CTL operations
+#define EARLYCON_CTL_STATUS 1  // earlycon_status_t *
+#define EARLYCON_CTL_READ 2    // offset, buffer, size; returns bytes copied
+#define EARLYCON_CTL_RELEASE 3 // returns the offset drawing stopped at
+
+    typedef struct earlycon_status
+    {
+        uint32_t flags;
+        uint32_t columns; // Text grid, 0 when not attached
+        uint32_t rows;
+        uint32_t column; // Cursor
+        uint32_t row;
+        uint32_t cell_width; // Pixels per character cell
+        uint32_t cell_height;
+        uint32_t scrollback_size;
+        uint64_t written; // Bytes ever written; the offset of the next one
+        uint64_t oldest;  // Offset of the oldest byte still in the ring
+    } earlycon_status_t;
+
+    // Record a console character and draw it if attached. Safe to call
+    // before anything is initialized; carriage returns are dropped
+    void earlycon_putchar(char c);
+    void earlycon_puts(const char *str);
+
+    // Start drawing on the loader's framebuffer, replaying the last
+    // screenful of the scrollback. Fails for pixel formats other than
+    // 16, 24 and 32 bits per pixel
+    int earlycon_attach(const boot_framebuffer_t *framebuffer);
+
+    // Copy recorded output from `offset` on, clamped to what the ring
+    // still holds; returns the bytes copied
+    size_t earlycon_read(uint64_t offset, char *buffer, size_t size);
+
+    void earlycon_status(earlycon_status_t *status);
+
+    // Stop drawing, leaving the screen to the console server; returns
+    // the offset of the first byte that was not drawn
+    uint64_t earlycon_release(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_EARLYCON_H
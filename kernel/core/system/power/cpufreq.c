@@ -0,0 +1,453 @@
+/*
+ * Orion Operating System - CPU Frequency Scaling
+ *
+ * Policy bookkeeping, governors and attributes. Utilization is an average
+ * of the scheduler ticks a CPU spent running threads, decayed by 1/32 per
+ * tick so it follows a load change within a few tens of ticks. The
+ * governor runs from the tick of the CPU it controls, which is also where
+ * the scaling driver must program its per-CPU registers; changes of the
+ * limits made from another CPU are picked up on the owner's next tick.
+ *
+ * Governors:
+ *   performance  always the highest allowed frequency
+ *   powersave    always the lowest allowed frequency
+ *   schedutil    a frequency proportional to utilization, with 25% headroom
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/klog.h>
+#include <orion/wallclock.h>
+#include "cpufreq.h"
+
+// Utilization decay per tick, as a shift (1/32)
+#define CPUFREQ_UTIL_DECAY_SHIFT 5
+
+extern uint32_t arch_get_current_cpu(void);
+
+static const cpufreq_driver_t *g_driver;
+static cpufreq_policy_t g_policies[CPUFREQ_MAX_CPUS];
+static spinlock_t g_cpufreq_lock = SPINLOCK_INIT;
+
+// ========================================
+// GOVERNORS
+// ========================================
+
+static uint32_t governor_performance(const cpufreq_policy_t *policy, uint32_t util)
+{
+    (void)util;
+    return policy->cpuinfo_max_khz;
+}
+
+static uint32_t governor_powersave(const cpufreq_policy_t *policy, uint32_t util)
+{
+    (void)util;
+    return policy->cpuinfo_min_khz;
+}
+
+static uint32_t governor_schedutil(const cpufreq_policy_t *policy, uint32_t util)
+{
+    uint64_t khz = (uint64_t)policy->cpuinfo_max_khz * util * CPUFREQ_HEADROOM_NUM /
+                   (CPUFREQ_UTIL_SCALE * CPUFREQ_HEADROOM_DEN);
+    return (uint32_t)khz;
+}
+
+static const cpufreq_governor_t g_governors[] = {
+    {"schedutil", governor_schedutil},
+    {"performance", governor_performance},
+    {"powersave", governor_powersave},
+};
+
+#define CPUFREQ_GOVERNOR_COUNT (sizeof(g_governors) / sizeof(g_governors[0]))
+
+static const cpufreq_governor_t *find_governor(const char *name)
+{
+    for (size_t i = 0; i < CPUFREQ_GOVERNOR_COUNT; i++)
+    {
+        if (strcmp(g_governors[i].name, name) == 0)
+        {
+            return &g_governors[i];
+        }
+    }
+    return NULL;
+}
+
+// ========================================
+// POLICIES
+// ========================================
+
+int cpufreq_register_driver(const cpufreq_driver_t *driver)
+{
+    if (!driver || !driver->init || !driver->set_target)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_cpufreq_lock);
+    if (g_driver)
+    {
+        spinlock_unlock(&g_cpufreq_lock);
+        return -OR_EBUSY;
+    }
+    g_driver = driver;
+    spinlock_unlock(&g_cpufreq_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "cpufreq: scaling driver %s", driver->name);
+    return OR_OK;
+}
+
+int cpufreq_init_cpu(void)
+{
+    uint32_t cpu = arch_get_current_cpu();
+    if (!g_driver)
+    {
+        return -OR_ENODEV;
+    }
+    if (cpu >= CPUFREQ_MAX_CPUS)
+    {
+        return -OR_EINVAL;
+    }
+
+    cpufreq_policy_t *policy = &g_policies[cpu];
+    memset(policy, 0, sizeof(*policy));
+    policy->cpu = cpu;
+    int result = g_driver->init(policy);
+    if (result != OR_OK || policy->cpuinfo_min_khz == 0 || policy->cpuinfo_min_khz > policy->cpuinfo_max_khz)
+    {
+        klog_warning(KLOG_CAT_KERNEL, "cpufreq: CPU %u not scalable (%d)", cpu, result);
+        return result != OR_OK ? result : -OR_ENODEV;
+    }
+
+    policy->min_khz = policy->cpuinfo_min_khz;
+    policy->max_khz = policy->cpuinfo_max_khz;
+    policy->rate_limit_ns = CPUFREQ_DEFAULT_RATE_LIMIT_NS;
+    policy->governor = &g_governors[0];
+    policy->limits_changed = true;
+
+    spinlock_lock(&g_cpufreq_lock);
+    policy->active = true;
+    spinlock_unlock(&g_cpufreq_lock);
+
+    klog_info(KLOG_CAT_KERNEL, "cpufreq: CPU %u %u-%u kHz, governor %s", cpu, policy->cpuinfo_min_khz,
+              policy->cpuinfo_max_khz, policy->governor->name);
+    return OR_OK;
+}
+
+// Clamp a governor's choice into the administrator and thermal limits
+static uint32_t clamp_target(const cpufreq_policy_t *policy, uint32_t khz, uint32_t *min_khz, uint32_t *max_khz)
+{
+    uint32_t max = policy->max_khz;
+    if (policy->thermal_max_khz != 0 && policy->thermal_max_khz < max)
+    {
+        max = policy->thermal_max_khz;
+    }
+    // A thermal ceiling below the administrator's floor wins
+    uint32_t min = policy->min_khz < max ? policy->min_khz : max;
+
+    *min_khz = min;
+    *max_khz = max;
+    if (khz < min)
+    {
+        return min;
+    }
+    return khz > max ? max : khz;
+}
+
+void cpufreq_update_util(bool busy)
+{
+    uint32_t cpu = arch_get_current_cpu();
+    if (cpu >= CPUFREQ_MAX_CPUS || !g_policies[cpu].active)
+    {
+        return;
+    }
+
+    cpufreq_policy_t *policy = &g_policies[cpu];
+    policy->util -= policy->util >> CPUFREQ_UTIL_DECAY_SHIFT;
+    if (busy)
+    {
+        policy->util += CPUFREQ_UTIL_SCALE >> CPUFREQ_UTIL_DECAY_SHIFT;
+    }
+
+    uint64_t now = wallclock_monotonic_ns();
+    // The sensor MSRs are per-CPU too, so the owning CPU samples them
+    if (g_driver->read_temp && now - policy->last_temp_ns >= CPUFREQ_TEMP_INTERVAL_NS)
+    {
+        int32_t millicelsius;
+        policy->temp_valid = g_driver->read_temp(policy, &millicelsius) == OR_OK;
+        policy->temp_mc = millicelsius;
+        policy->last_temp_ns = now;
+    }
+
+    spinlock_lock(&g_cpufreq_lock);
+    bool forced = policy->limits_changed;
+    if (!forced && now - policy->last_change_ns < policy->rate_limit_ns)
+    {
+        spinlock_unlock(&g_cpufreq_lock);
+        return;
+    }
+    uint32_t min_khz, max_khz;
+    uint32_t target = clamp_target(policy, policy->governor->next_khz(policy, policy->util), &min_khz, &max_khz);
+    policy->limits_changed = false;
+    spinlock_unlock(&g_cpufreq_lock);
+
+    if (target == policy->cur_khz && !forced)
+    {
+        return;
+    }
+    if (g_driver->set_target(policy, target, min_khz, max_khz) == OR_OK)
+    {
+        policy->cur_khz = target;
+        policy->last_change_ns = now;
+    }
+}
+
+int cpufreq_set_thermal_limit(uint32_t cpu, uint32_t max_khz)
+{
+    if (cpu >= CPUFREQ_MAX_CPUS || !g_policies[cpu].active)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_cpufreq_lock);
+    g_policies[cpu].thermal_max_khz = max_khz;
+    g_policies[cpu].limits_changed = true;
+    spinlock_unlock(&g_cpufreq_lock);
+    return OR_OK;
+}
+
+int cpufreq_get_range(uint32_t cpu, uint32_t *min_khz, uint32_t *max_khz)
+{
+    if (cpu >= CPUFREQ_MAX_CPUS || !g_policies[cpu].active)
+    {
+        return -OR_EINVAL;
+    }
+    *min_khz = g_policies[cpu].cpuinfo_min_khz;
+    *max_khz = g_policies[cpu].cpuinfo_max_khz;
+    return OR_OK;
+}
+
+// ========================================
+// ATTRIBUTES
+// ========================================
+
+// Attributes holding a frequency are in kHz, as in Linux sysfs
+enum cpufreq_attr
+{
+    ATTR_CPUINFO_MIN_FREQ,
+    ATTR_CPUINFO_MAX_FREQ,
+    ATTR_SCALING_CUR_FREQ,
+    ATTR_SCALING_MIN_FREQ,
+    ATTR_SCALING_MAX_FREQ,
+    ATTR_SCALING_GOVERNOR,
+    ATTR_SCALING_AVAILABLE_GOVERNORS,
+    ATTR_SCALING_DRIVER,
+    ATTR_THERMAL_MAX_FREQ,
+    ATTR_UTILIZATION,
+    ATTR_RATE_LIMIT_US,
+    ATTR_TEMPERATURE,
+};
+
+static const struct
+{
+    const char *name;
+    enum cpufreq_attr attr;
+    bool writable;
+} g_attributes[] = {
+    {"cpuinfo_min_freq", ATTR_CPUINFO_MIN_FREQ, false},
+    {"cpuinfo_max_freq", ATTR_CPUINFO_MAX_FREQ, false},
+    {"scaling_cur_freq", ATTR_SCALING_CUR_FREQ, false},
+    {"scaling_min_freq", ATTR_SCALING_MIN_FREQ, true},
+    {"scaling_max_freq", ATTR_SCALING_MAX_FREQ, true},
+    {"scaling_governor", ATTR_SCALING_GOVERNOR, true},
+    {"scaling_available_governors", ATTR_SCALING_AVAILABLE_GOVERNORS, false},
+    {"scaling_driver", ATTR_SCALING_DRIVER, false},
+    {"thermal_max_freq", ATTR_THERMAL_MAX_FREQ, true},
+    {"utilization", ATTR_UTILIZATION, false},
+    {"rate_limit_us", ATTR_RATE_LIMIT_US, true},
+    {"temperature", ATTR_TEMPERATURE, false},
+};
+
+#define CPUFREQ_ATTR_COUNT (sizeof(g_attributes) / sizeof(g_attributes[0]))
+
+static int find_attribute(const char *name, bool *writable)
+{
+    for (size_t i = 0; i < CPUFREQ_ATTR_COUNT; i++)
+    {
+        if (strcmp(g_attributes[i].name, name) == 0)
+        {
+            *writable = g_attributes[i].writable;
+            return (int)g_attributes[i].attr;
+        }
+    }
+    return -OR_ENOENT;
+}
+
+// Decimal value of a whole string, trailing newline allowed
+static int parse_u32(const char *text, uint32_t *value)
+{
+    uint64_t result = 0;
+    if (*text < '0' || *text > '9')
+    {
+        return -OR_EINVAL;
+    }
+    for (; *text >= '0' && *text <= '9'; text++)
+    {
+        result = result * 10 + (uint64_t)(*text - '0');
+        if (result > 0xFFFFFFFFULL)
+        {
+            return -OR_EINVAL;
+        }
+    }
+    if (*text == '\n')
+    {
+        text++;
+    }
+    if (*text != '\0')
+    {
+        return -OR_EINVAL;
+    }
+    *value = (uint32_t)result;
+    return OR_OK;
+}
+
+int cpufreq_attr_show(uint32_t cpu, const char *name, char *buf, size_t size)
+{
+    bool writable;
+    int attr = find_attribute(name, &writable);
+    if (attr < 0)
+    {
+        return attr;
+    }
+    if (cpu >= CPUFREQ_MAX_CPUS || !g_policies[cpu].active || !buf || size == 0)
+    {
+        return -OR_EINVAL;
+    }
+
+    const cpufreq_policy_t *policy = &g_policies[cpu];
+    switch ((enum cpufreq_attr)attr)
+    {
+    case ATTR_CPUINFO_MIN_FREQ:
+        return snprintf(buf, size, "%u\n", policy->cpuinfo_min_khz);
+    case ATTR_CPUINFO_MAX_FREQ:
+        return snprintf(buf, size, "%u\n", policy->cpuinfo_max_khz);
+    case ATTR_SCALING_CUR_FREQ:
+        return snprintf(buf, size, "%u\n", policy->cur_khz);
+    case ATTR_SCALING_MIN_FREQ:
+        return snprintf(buf, size, "%u\n", policy->min_khz);
+    case ATTR_SCALING_MAX_FREQ:
+        return snprintf(buf, size, "%u\n", policy->max_khz);
+    case ATTR_SCALING_GOVERNOR:
+        return snprintf(buf, size, "%s\n", policy->governor->name);
+    case ATTR_SCALING_AVAILABLE_GOVERNORS:
+        return snprintf(buf, size, "%s %s %s\n", g_governors[0].name, g_governors[1].name, g_governors[2].name);
+    case ATTR_SCALING_DRIVER:
+        return snprintf(buf, size, "%s\n", g_driver->name);
+    case ATTR_THERMAL_MAX_FREQ:
+        return snprintf(buf, size, "%u\n", policy->thermal_max_khz);
+    case ATTR_UTILIZATION:
+        return snprintf(buf, size, "%u\n", policy->util);
+    case ATTR_RATE_LIMIT_US:
+        return snprintf(buf, size, "%u\n", (uint32_t)(policy->rate_limit_ns / 1000));
+    case ATTR_TEMPERATURE:
+        if (!policy->temp_valid)
+        {
+            return -OR_ENODEV;
+        }
+        return snprintf(buf, size, "%d\n", policy->temp_mc);
+    }
+    return -OR_ENOENT;
+}
+
+int cpufreq_attr_store(uint32_t cpu, const char *name, const char *value)
+{
+    bool writable;
+    int attr = find_attribute(name, &writable);
+    if (attr < 0)
+    {
+        return attr;
+    }
+    if (!writable)
+    {
+        return -OR_EPERM;
+    }
+    if (cpu >= CPUFREQ_MAX_CPUS || !g_policies[cpu].active || !value)
+    {
+        return -OR_EINVAL;
+    }
+
+    cpufreq_policy_t *policy = &g_policies[cpu];
+    const cpufreq_governor_t *governor = NULL;
+    uint32_t number = 0;
+    if (attr == ATTR_SCALING_GOVERNOR)
+    {
+        char name_buf[CPUFREQ_NAME_LEN];
+        size_t length = 0;
+        while (value[length] && value[length] != '\n' && length < CPUFREQ_NAME_LEN - 1)
+        {
+            name_buf[length] = value[length];
+            length++;
+        }
+        name_buf[length] = '\0';
+        governor = find_governor(name_buf);
+        if (!governor)
+        {
+            return -OR_EINVAL;
+        }
+    }
+    else if (parse_u32(value, &number) != OR_OK)
+    {
+        return -OR_EINVAL;
+    }
+
+    int result = OR_OK;
+    spinlock_lock(&g_cpufreq_lock);
+    switch ((enum cpufreq_attr)attr)
+    {
+    case ATTR_SCALING_MIN_FREQ:
+        if (number < policy->cpuinfo_min_khz || number > policy->max_khz)
+        {
+            result = -OR_EINVAL;
+            break;
+        }
+        policy->min_khz = number;
+        break;
+    case ATTR_SCALING_MAX_FREQ:
+        if (number > policy->cpuinfo_max_khz || number < policy->min_khz)
+        {
+            result = -OR_EINVAL;
+            break;
+        }
+        policy->max_khz = number;
+        break;
+    case ATTR_SCALING_GOVERNOR:
+        policy->governor = governor;
+        break;
+    case ATTR_RATE_LIMIT_US:
+        policy->rate_limit_ns = (uint64_t)number * 1000;
+        break;
+    case ATTR_THERMAL_MAX_FREQ:
+        // Written by the thermal service; 0 lifts the ceiling
+        if (number != 0 && number < policy->cpuinfo_min_khz)
+        {
+            result = -OR_EINVAL;
+            break;
+        }
+        policy->thermal_max_khz = number;
+        break;
+    default:
+        result = -OR_EPERM;
+        break;
+    }
+    if (result == OR_OK)
+    {
+        policy->limits_changed = true;
+    }
+    spinlock_unlock(&g_cpufreq_lock);
+    return result;
+}
@@ -0,0 +1,147 @@
+/*
+ * Orion Operating System - CPU Frequency Scaling Header
+ *
+ * Every CPU has a frequency policy: the hardware range reported by the
+ * scaling driver (Intel P-state or AMD CPPC on x86_64), the range allowed
+ * by the administrator, a ceiling set by the thermal subsystem and a
+ * governor choosing the frequency inside them. Governors are fed the
+ * utilization the scheduler measures on each tick. Policies are read and
+ * tuned through named attributes in the style of sysfs, with SYS_CPUFREQ.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_CPUFREQ_H
+#define ORION_CPUFREQ_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define CPUFREQ_MAX_CPUS 64
+#define CPUFREQ_NAME_LEN 16
+
+// Utilization scale: a CPU busy on every tick has this utilization
+#define CPUFREQ_UTIL_SCALE 1024
+
+// schedutil leaves this much headroom above the measured utilization (1.25x)
+#define CPUFREQ_HEADROOM_NUM 5
+#define CPUFREQ_HEADROOM_DEN 4
+
+// Shortest interval between two frequency changes requested by a governor
+#define CPUFREQ_DEFAULT_RATE_LIMIT_NS 10000000ULL
+
+// Interval between two readings of the CPU temperature sensor
+#define CPUFREQ_TEMP_INTERVAL_NS 1000000000ULL
+
+// SYS_CPUFREQ operations
+#define CPUFREQ_OP_SHOW 1  // Copy an attribute's text into a user buffer
+#define CPUFREQ_OP_STORE 2 // Set an attribute from user text
+
+    struct cpufreq_policy;
+
+    // Scaling driver. Callbacks run on the CPU owning the policy, as the
+    // frequency controls of x86 processors are per-CPU MSRs
+    typedef struct cpufreq_driver
+    {
+        char name[CPUFREQ_NAME_LEN];
+        // Fill cpuinfo_min_khz/cpuinfo_max_khz and enable the hardware
+        int (*init)(struct cpufreq_policy *policy);
+        // Program the frequency closest to target_khz within [min_khz, max_khz]
+        int (*set_target)(struct cpufreq_policy *policy, uint32_t target_khz, uint32_t min_khz, uint32_t max_khz);
+        // Optional: temperature of the CPU in millidegrees Celsius
+        int (*read_temp)(struct cpufreq_policy *policy, int32_t *millicelsius);
+    } cpufreq_driver_t;
+
+    typedef struct cpufreq_governor
+    {
+        char name[CPUFREQ_NAME_LEN];
+        // Frequency wanted for the utilization, before the policy limits
+        uint32_t (*next_khz)(const struct cpufreq_policy *policy, uint32_t util);
+    } cpufreq_governor_t;
+
+    typedef struct cpufreq_policy
+    {
+        uint32_t cpu;
+        bool active;
+        uint32_t cpuinfo_min_khz; // Hardware range
+        uint32_t cpuinfo_max_khz;
+        uint32_t min_khz; // Range allowed by the administrator
+        uint32_t max_khz;
+        uint32_t thermal_max_khz; // Ceiling of the thermal subsystem
+        uint32_t cur_khz;         // Last frequency programmed
+        uint32_t util;            // Utilization, 0..CPUFREQ_UTIL_SCALE
+        uint64_t rate_limit_ns;
+        uint64_t last_change_ns;
+        bool limits_changed; // Reprogram on the next tick even if idle
+        bool temp_valid;
+        int32_t temp_mc; // Last sensor reading, millidegrees Celsius
+        uint64_t last_temp_ns;
+        const cpufreq_governor_t *governor;
+        uint64_t driver_data[4]; // Per-CPU state of the scaling driver
+    } cpufreq_policy_t;
+
+    /**
+     * Register the scaling driver; only one may be registered
+     *
+     * @return 0 on success, -OR_EBUSY if a driver is already registered
+     */
+    int cpufreq_register_driver(const cpufreq_driver_t *driver);
+
+    /**
+     * Set up the policy of the calling CPU, to be run by each CPU as it
+     * comes up once the scaling driver is registered
+     *
+     * @return 0 on success, -OR_ENODEV without a scaling driver
+     */
+    int cpufreq_init_cpu(void);
+
+    /**
+     * Account one scheduler tick of the calling CPU and let its governor
+     * adjust the frequency
+     *
+     * @param busy Whether a thread was running during the tick
+     */
+    void cpufreq_update_util(bool busy);
+
+    /**
+     * Cap the frequency of a CPU for thermal reasons; 0 lifts the cap
+     *
+     * @return 0 on success, -OR_EINVAL for an unknown CPU
+     */
+    int cpufreq_set_thermal_limit(uint32_t cpu, uint32_t max_khz);
+
+    /**
+     * Frequency range of a CPU, for the thermal subsystem's cooling steps
+     *
+     * @return 0 on success, -OR_EINVAL for an unknown CPU
+     */
+    int cpufreq_get_range(uint32_t cpu, uint32_t *min_khz, uint32_t *max_khz);
+
+    /**
+     * Text of a policy attribute (scaling_governor, scaling_min_freq...)
+     *
+     * @return Length written (NUL excluded), negative error code on failure
+     */
+    int cpufreq_attr_show(uint32_t cpu, const char *name, char *buf, size_t size);
+
+    /**
+     * Set a writable policy attribute from its text
+     *
+     * @return 0 on success, -OR_EINVAL for a bad value, -OR_EPERM for a
+     *         read-only attribute, -OR_ENOENT for an unknown one
+     */
+    int cpufreq_attr_store(uint32_t cpu, const char *name, const char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_CPUFREQ_H
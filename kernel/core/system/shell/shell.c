@@ -123,7 +123,16 @@ int cmd_profile(int argc, char* argv[]) {
     kinfo("Profile stub"); 
     return 0; 
 }
+extern int ipc_benchmark(uint32_t iterations);
+
 int cmd_benchmark(int argc, char* argv[]) { 
+    if (argc >= 2 && strcmp(argv[1], "ipc") == 0) {
+        uint32_t iterations = 0;
+        for (const char* digit = argc >= 3 ? argv[2] : ""; *digit >= '0' && *digit <= '9'; digit++) {
+            iterations = iterations * 10 + (uint32_t)(*digit - '0');
+        }
+        return ipc_benchmark(iterations) == OR_OK ? 0 : -1;
+    }
     kinfo("Benchmark stub"); 
     return 0; 
 }
@@ -350,7 +359,8 @@ static const struct shell_command builtin_commands[] = {
                 "  memory    Memory bandwidth\n"
                 "  disk      Disk I/O\n"
                 "  network   Network throughput\n"
-                "  syscall   System call latency",
+                "  syscall   System call latency\n"
+                "  ipc       IPC round trip, send+recv against call/reply",
         .handler = cmd_benchmark,
         .flags = SHELL_CMD_FLAG_DEBUG,
         .min_args = 0,
@@ -0,0 +1,196 @@
+/*
+ * Orion Operating System - Wall Clock
+ *
+ * The wall clock is the monotonic clock plus an offset. Slewing and leap
+ * seconds are applied lazily: every read first folds the part of the
+ * pending correction allowed by the time elapsed since the previous read
+ * into the offset, then applies a due leap second, so no timer is needed
+ * and readers always see a consistent clock.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include "wallclock.h"
+
+#define NS_PER_DAY (86400ULL * WALLCLOCK_NS_PER_SEC)
+
+extern uint64_t arch_get_timestamp(void);
+extern uint64_t arch_get_timestamp_frequency(void);
+extern uint64_t arch_get_boot_time(void);
+
+static struct
+{
+    int64_t offset_ns;   // Realtime minus monotonic
+    int64_t pending_ns;  // Slew still to absorb
+    uint64_t updated_ns; // Monotonic time of the last update
+    uint64_t leap_at_ns; // Realtime at which the pending leap second is due
+    uint64_t steps;
+    uint64_t leaps;
+    uint32_t leap;
+    uint32_t flags;
+} g_wallclock;
+
+static spinlock_t g_wallclock_lock = SPINLOCK_INIT;
+
+uint64_t wallclock_monotonic_ns(void)
+{
+    uint64_t ticks = arch_get_timestamp();
+    uint64_t frequency = arch_get_timestamp_frequency();
+
+    // Split to avoid overflowing 64 bits after a few seconds of uptime
+    return (ticks / frequency) * WALLCLOCK_NS_PER_SEC + (ticks % frequency) * WALLCLOCK_NS_PER_SEC / frequency;
+}
+
+// The kernel does not suspend yet, so no time is spent suspended
+uint64_t wallclock_boottime_ns(void)
+{
+    return wallclock_monotonic_ns();
+}
+
+// Fold the allowed part of the pending slew and a due leap second into the
+// offset. Called with the lock held
+static uint64_t wallclock_update(uint64_t now)
+{
+    uint64_t elapsed = now - g_wallclock.updated_ns;
+    g_wallclock.updated_ns = now;
+
+    if (g_wallclock.pending_ns != 0)
+    {
+        uint64_t budget = (elapsed / 1000000) * WALLCLOCK_MAX_SLEW_PPM +
+                          (elapsed % 1000000) * WALLCLOCK_MAX_SLEW_PPM / 1000000;
+        int64_t pending = g_wallclock.pending_ns;
+        uint64_t magnitude = pending < 0 ? (uint64_t)(-pending) : (uint64_t)pending;
+        int64_t applied = (int64_t)(magnitude < budget ? magnitude : budget);
+        if (pending < 0)
+        {
+            applied = -applied;
+        }
+        g_wallclock.offset_ns += applied;
+        g_wallclock.pending_ns -= applied;
+    }
+
+    uint64_t realtime = now + (uint64_t)g_wallclock.offset_ns;
+
+    // Insertion repeats the last second of the day, deletion skips it
+    if (g_wallclock.leap == WALLCLOCK_LEAP_INSERT && realtime >= g_wallclock.leap_at_ns)
+    {
+        g_wallclock.offset_ns -= (int64_t)WALLCLOCK_NS_PER_SEC;
+        g_wallclock.leap = WALLCLOCK_LEAP_NONE;
+        g_wallclock.leaps++;
+        kinfo("wallclock: leap second inserted");
+    }
+    else if (g_wallclock.leap == WALLCLOCK_LEAP_DELETE &&
+             realtime >= g_wallclock.leap_at_ns - WALLCLOCK_NS_PER_SEC)
+    {
+        g_wallclock.offset_ns += (int64_t)WALLCLOCK_NS_PER_SEC;
+        g_wallclock.leap = WALLCLOCK_LEAP_NONE;
+        g_wallclock.leaps++;
+        kinfo("wallclock: leap second deleted");
+    }
+
+    return now + (uint64_t)g_wallclock.offset_ns;
+}
+
+uint64_t wallclock_realtime_ns(void)
+{
+    uint64_t now = wallclock_monotonic_ns();
+
+    spinlock_lock(&g_wallclock_lock);
+    uint64_t realtime = wallclock_update(now);
+    spinlock_unlock(&g_wallclock_lock);
+
+    return realtime;
+}
+
+int wallclock_slew(int64_t offset_ns)
+{
+    uint64_t now = wallclock_monotonic_ns();
+
+    spinlock_lock(&g_wallclock_lock);
+    wallclock_update(now);
+    g_wallclock.pending_ns = offset_ns;
+    g_wallclock.flags |= WALLCLOCK_FLAG_SYNCED;
+    spinlock_unlock(&g_wallclock_lock);
+
+    return OR_OK;
+}
+
+int wallclock_step(int64_t offset_ns)
+{
+    uint64_t now = wallclock_monotonic_ns();
+
+    spinlock_lock(&g_wallclock_lock);
+    uint64_t realtime = wallclock_update(now);
+    if (offset_ns < 0 && (uint64_t)(-offset_ns) > realtime)
+    {
+        spinlock_unlock(&g_wallclock_lock);
+        return -OR_EINVAL;
+    }
+
+    g_wallclock.offset_ns += offset_ns;
+    g_wallclock.pending_ns = 0;
+    g_wallclock.steps++;
+    g_wallclock.flags |= WALLCLOCK_FLAG_SYNCED;
+
+    // A pending leap second stays attached to the end of the (new) day
+    realtime += (uint64_t)offset_ns;
+    g_wallclock.leap_at_ns = (realtime / NS_PER_DAY + 1) * NS_PER_DAY;
+    spinlock_unlock(&g_wallclock_lock);
+
+    kinfo("wallclock: stepped by %lld ns", (long long)offset_ns);
+    return OR_OK;
+}
+
+int wallclock_set_leap(uint32_t leap)
+{
+    if (leap > WALLCLOCK_LEAP_DELETE)
+    {
+        return -OR_EINVAL;
+    }
+
+    uint64_t now = wallclock_monotonic_ns();
+
+    spinlock_lock(&g_wallclock_lock);
+    uint64_t realtime = wallclock_update(now);
+    g_wallclock.leap = leap;
+    g_wallclock.leap_at_ns = (realtime / NS_PER_DAY + 1) * NS_PER_DAY;
+    spinlock_unlock(&g_wallclock_lock);
+
+    return OR_OK;
+}
+
+void wallclock_get_status(wallclock_status_t *status)
+{
+    uint64_t now = wallclock_monotonic_ns();
+
+    spinlock_lock(&g_wallclock_lock);
+    status->realtime_ns = wallclock_update(now);
+    status->pending_slew_ns = g_wallclock.pending_ns;
+    status->steps = g_wallclock.steps;
+    status->leaps = g_wallclock.leaps;
+    status->leap = g_wallclock.leap;
+    status->flags = g_wallclock.flags;
+    spinlock_unlock(&g_wallclock_lock);
+}
+
+void wallclock_init(void)
+{
+    uint64_t now = wallclock_monotonic_ns();
+    uint64_t boot_time = arch_get_boot_time();
+
+    spinlock_lock(&g_wallclock_lock);
+    g_wallclock.offset_ns = (int64_t)(boot_time * WALLCLOCK_NS_PER_SEC - now);
+    g_wallclock.pending_ns = 0;
+    g_wallclock.updated_ns = now;
+    g_wallclock.leap = WALLCLOCK_LEAP_NONE;
+    g_wallclock.flags = 0;
+    spinlock_unlock(&g_wallclock_lock);
+
+    kinfo("wallclock: seeded from RTC at %llu s", (unsigned long long)boot_time);
+}
@@ -0,0 +1,120 @@
+/*
+ * Orion Operating System - Wall Clock Header
+ *
+ * UTC wall clock kept as an offset from the monotonic clock. The offset is
+ * seeded from the RTC at boot and disciplined by the NTP service, which
+ * either slews it (the clock runs slightly faster or slower until a pending
+ * correction is absorbed) or steps it. A leap second announced by the time
+ * source is applied at the end of the UTC day. Adjustments are made with
+ * SYS_CLOCK_ADJUST.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_WALLCLOCK_H
+#define ORION_WALLCLOCK_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+// Clock identifiers of SYS_CLOCK_GET
+#define CLOCK_ID_MONOTONIC 0 // Nanoseconds since boot, never adjusted
+#define CLOCK_ID_REALTIME 1  // Nanoseconds since the Unix epoch (UTC)
+#define CLOCK_ID_BOOTTIME 2  // Nanoseconds since boot, time suspended included
+
+// SYS_CLOCK_ADJUST operations
+#define CLOCK_ADJUST_SLEW 1   // Absorb an offset gradually (arg: signed ns)
+#define CLOCK_ADJUST_STEP 2   // Apply an offset at once (arg: signed ns)
+#define CLOCK_ADJUST_LEAP 3   // Announce a leap second (arg: WALLCLOCK_LEAP_*)
+#define CLOCK_ADJUST_STATUS 4 // Copy a wallclock_status_t (arg: pointer)
+
+// Leap second indicator, as carried by NTP
+#define WALLCLOCK_LEAP_NONE 0   // No leap second pending
+#define WALLCLOCK_LEAP_INSERT 1 // Last minute of the day has 61 seconds
+#define WALLCLOCK_LEAP_DELETE 2 // Last minute of the day has 59 seconds
+
+// Status flags
+#define WALLCLOCK_FLAG_SYNCED (1 << 0) // Adjusted by a time source since boot
+
+#define WALLCLOCK_NS_PER_SEC 1000000000ULL
+
+// Largest slew rate in parts per million (0.5 ms per second)
+#define WALLCLOCK_MAX_SLEW_PPM 500
+
+    // Wall clock state, also the CLOCK_ADJUST_STATUS ABI
+    typedef struct wallclock_status
+    {
+        uint64_t realtime_ns;    // Current UTC time
+        int64_t pending_slew_ns; // Correction not yet absorbed
+        uint64_t steps;          // Step adjustments since boot
+        uint64_t leaps;          // Leap seconds applied since boot
+        uint32_t leap;           // Pending WALLCLOCK_LEAP_* indicator
+        uint32_t flags;          // WALLCLOCK_FLAG_*
+    } wallclock_status_t;
+
+    /**
+     * Seed the wall clock from the RTC
+     */
+    void wallclock_init(void);
+
+    /**
+     * Nanoseconds since boot
+     */
+    uint64_t wallclock_monotonic_ns(void);
+
+    /**
+     * Nanoseconds since boot, time suspended included
+     */
+    uint64_t wallclock_boottime_ns(void);
+
+    /**
+     * Current UTC time in nanoseconds since the Unix epoch
+     */
+    uint64_t wallclock_realtime_ns(void);
+
+    /**
+     * Absorb an offset gradually, at most WALLCLOCK_MAX_SLEW_PPM
+     *
+     * Replaces any correction still pending.
+     *
+     * @param offset_ns Signed correction in nanoseconds
+     * @return 0 on success, negative error code on failure
+     */
+    int wallclock_slew(int64_t offset_ns);
+
+    /**
+     * Apply an offset at once, cancelling any pending slew
+     *
+     * @param offset_ns Signed correction in nanoseconds
+     * @return 0 on success, negative error code on failure
+     */
+    int wallclock_step(int64_t offset_ns);
+
+    /**
+     * Announce (or withdraw) a leap second at the end of the current UTC day
+     *
+     * @param leap WALLCLOCK_LEAP_* indicator
+     * @return 0 on success, -OR_EINVAL for an unknown indicator
+     */
+    int wallclock_set_leap(uint32_t leap);
+
+    /**
+     * Snapshot the wall clock state
+     *
+     * @param status Output state
+     */
+    void wallclock_get_status(wallclock_status_t *status);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_WALLCLOCK_H
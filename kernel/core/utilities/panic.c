@@ -3,10 +3,56 @@
 #include <orion/types.h>
 #include <orion/klog.h>
 #include <orion/structures.h
+#include <orion/panic.h>
+#include <orion/scheduler.h>
+#include <orion/wallclock.h>
 
 // Panic state
 static bool panic_in_progress = false;
 
+// Classification of the fault the next core dump is written for
+static crash_fault_t crash_fault = {0};
+
+// Captures spooled since boot; crashd clears the sequence of the spool
+// file once it stored the capture
+static uint64_t crash_spool_sequence = 0;
+
+void crash_record_fault(crash_fault_class_t classification, uint64_t address, uint64_t pid, const char *detail)
+{
+    crash_fault.classification = classification;
+    crash_fault.address = address;
+    crash_fault.pid = pid;
+    snprintf(crash_fault.detail, sizeof(crash_fault.detail), "%s", detail ? detail : "");
+}
+
+const char *crash_fault_class_name(crash_fault_class_t classification)
+{
+    switch (classification)
+    {
+    case CRASH_FAULT_NONE:
+        return "none";
+    case CRASH_FAULT_PAGE:
+        return "page fault";
+    case CRASH_FAULT_GUARD_PAGE:
+        return "guard page hit";
+    case CRASH_FAULT_OOM:
+        return "out of memory";
+    }
+    return "unknown";
+}
+
+// Component a capture is filed under: the faulting process, or the kernel
+static void spool_component(uint64_t pid, char component[CRASH_COMPONENT_LEN])
+{
+    proc_info_t info;
+    if (pid != 0 && scheduler_get_process_info(pid - 1, &info) == OR_OK && info.pid == pid && info.name[0])
+    {
+        snprintf(component, CRASH_COMPONENT_LEN, "%s", info.name);
+        return;
+    }
+    snprintf(component, CRASH_COMPONENT_LEN, "%s", pid != 0 ? "process" : "kernel");
+}
+
 // Emergency system shutdown
 void emergency_halt(void)
 {
@@ -148,14 +194,10 @@ void save_core_dump(const char *file, int line, const char *function, const char
 {
     klog_emergency("Saving core dump...");
 
-    // Create core dump filename with timestamp
-    char core_filename[256];
     uint64_t timestamp = arch_get_timestamp();
-    snprintf(core_filename, sizeof(core_filename),
-             "core.%llu.%s.%d", timestamp, file, line);
 
-    // Core dump implementation with filesystem support
-    // Try to create a filesystem-based core dump, fallback to in-memory
+    // Core dump implementation with filesystem support: the capture goes
+    // to the spool file crashd takes it from, in-memory otherwise
 
     // Check if VFS is available and we can write to filesystem
     extern bool vfs_is_available(void);
@@ -165,61 +207,72 @@ void save_core_dump(const char *file, int line, const char *function, const char
 
     if (vfs_is_available())
     {
-        // Try to create filesystem-based core dump
-        kprintf("Creating filesystem core dump: %s\n", core_filename);
+        kprintf("Spooling core dump to %s\n", CRASH_SPOOL_PATH);
 
-        // Prepare core dump data in memory first
+        // Prepare core dump data in memory first, after the spool header
         char core_dump_buffer[4096];
+        crash_spool_header_t *header = (crash_spool_header_t *)core_dump_buffer;
+        char *text = core_dump_buffer + sizeof(*header);
+        size_t text_size = sizeof(core_dump_buffer) - sizeof(*header);
         size_t dump_size = 0;
 
+        memcpy(header->magic, CRASH_SPOOL_MAGIC, sizeof(header->magic));
+        header->sequence = ++crash_spool_sequence;
+        header->realtime_ns = wallclock_realtime_ns();
+        header->pid = panic_in_progress ? 0 : crash_fault.pid;
+        spool_component(header->pid, header->component);
+
         // Format core dump header
-        dump_size += snprintf(core_dump_buffer + dump_size,
-                              sizeof(core_dump_buffer) - dump_size,
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
                               "=== ORION OS CORE DUMP ===\n");
-        dump_size += snprintf(core_dump_buffer + dump_size,
-                              sizeof(core_dump_buffer) - dump_size,
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
                               "Timestamp: %llu\n", timestamp);
-        dump_size += snprintf(core_dump_buffer + dump_size,
-                              sizeof(core_dump_buffer) - dump_size,
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
                               "File: %s, Line: %d, Function: %s\n", file, line, function);
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
+                              "Reason: %s\n", fmt);
+        if (crash_fault.classification != CRASH_FAULT_NONE)
+        {
+            dump_size += snprintf(text + dump_size, text_size - dump_size,
+                                  "Fault: %s (%s) at 0x%llx, PID %llu\n",
+                                  crash_fault_class_name(crash_fault.classification), crash_fault.detail,
+                                  (unsigned long long)crash_fault.address, (unsigned long long)crash_fault.pid);
+        }
 
         // Add process information
         process_t *current = scheduler_get_current_process();
         if (current)
         {
-            dump_size += snprintf(core_dump_buffer + dump_size,
-                                  sizeof(core_dump_buffer) - dump_size,
+            dump_size += snprintf(text + dump_size, text_size - dump_size,
                                   "Process: PID %llu, State: %d\n",
                                   (unsigned long long)current->pid, current->state);
-            dump_size += snprintf(core_dump_buffer + dump_size,
-                                  sizeof(core_dump_buffer) - dump_size,
+            dump_size += snprintf(text + dump_size, text_size - dump_size,
                                   "Memory: estimated allocation\n");
         }
 
         // Add system state information
-        dump_size += snprintf(core_dump_buffer + dump_size,
-                              sizeof(core_dump_buffer) - dump_size,
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
                               "System uptime: %llu seconds\n",
                               (unsigned long long)(arch_get_timestamp() / 1000000000));
-        dump_size += snprintf(core_dump_buffer + dump_size,
-                              sizeof(core_dump_buffer) - dump_size,
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
                               "Memory: %llu MB free\n",
                               (unsigned long long)(pmm_get_free_pages() * PAGE_SIZE / (1024 * 1024)));
-        dump_size += snprintf(core_dump_buffer + dump_size,
-                              sizeof(core_dump_buffer) - dump_size,
+        dump_size += snprintf(text + dump_size, text_size - dump_size,
                               "Active processes: %u\n", scheduler_get_process_count());
+        header->length = (uint32_t)dump_size;
 
         // Try to write to filesystem
-        int write_result = vfs_write_file(core_filename, core_dump_buffer, dump_size);
-        if (write_result == (int)dump_size)
+        size_t spool_size = sizeof(*header) + dump_size;
+        int write_result = vfs_write_file(CRASH_SPOOL_PATH, core_dump_buffer, spool_size);
+        if (write_result == (int)spool_size)
         {
             fs_dump_created = true;
-            kprintf("Filesystem core dump created successfully: %s (%zu bytes)\n",
-                    core_filename, dump_size);
+            kprintf("Core dump %llu spooled successfully (%zu bytes)\n",
+                    (unsigned long long)header->sequence, dump_size);
         }
         else
         {
-            kwarn("Failed to create filesystem core dump (error: %d), falling back to in-memory",
+            kwarn("Failed to spool core dump (error: %d), falling back to in-memory",
                   write_result);
         }
     }
@@ -227,7 +280,14 @@ void save_core_dump(const char *file, int line, const char *function, const char
     // If filesystem dump failed or unavailable, create in-memory dump
     if (!fs_dump_created)
     {
-        kprintf("Creating in-memory core dump: %s\n", core_filename);
+        kprintf("Creating in-memory core dump at %llu\n", (unsigned long long)timestamp);
+        kprintf("Reason: %s\n", fmt);
+        if (crash_fault.classification != CRASH_FAULT_NONE)
+        {
+            kprintf("Fault: %s (%s) at 0x%llx, PID %llu\n",
+                    crash_fault_class_name(crash_fault.classification), crash_fault.detail,
+                    (unsigned long long)crash_fault.address, (unsigned long long)crash_fault.pid);
+        }
 
         // Dump current process state
         process_t *current = scheduler_get_current_process();
@@ -252,6 +312,9 @@ void save_core_dump(const char *file, int line, const char *function, const char
 
         kprintf("In-memory core dump completed\n");
     }
+
+    // The classification only describes this dump
+    crash_fault.classification = CRASH_FAULT_NONE;
 }
 
 // Macro for panic with location info
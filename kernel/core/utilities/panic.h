@@ -0,0 +1,71 @@
+/*
+ * Orion Operating System - Panic and Crash Dumps
+ *
+ * Fault handlers classify the fault they give up on before a core dump is
+ * written, so that dumps tell a deterministic guard page hit apart from an
+ * arbitrary bad access.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_PANIC_H
+#define ORION_PANIC_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define CRASH_FAULT_DETAIL_LEN 64
+#define CRASH_COMPONENT_LEN 32
+
+// Core dumps are written to this spool file, from which the crash dump
+// server (services/crashd) moves them to its reserved volume. A capture
+// is a crash_spool_header_t followed by `length` bytes of text
+#define CRASH_SPOOL_PATH "/var/crash/spool"
+#define CRASH_SPOOL_MAGIC "OKCR"
+
+    typedef enum crash_fault_class
+    {
+        CRASH_FAULT_NONE = 0,
+        CRASH_FAULT_PAGE,       // Unhandled page fault
+        CRASH_FAULT_GUARD_PAGE, // Access to a guard page around a stack or DMA buffer
+        CRASH_FAULT_OOM,        // Process killed by the out-of-memory handler
+    } crash_fault_class_t;
+
+    typedef struct crash_fault
+    {
+        crash_fault_class_t classification;
+        uint64_t address;
+        uint64_t pid;
+        char detail[CRASH_FAULT_DETAIL_LEN];
+    } crash_fault_t;
+
+    typedef struct crash_spool_header
+    {
+        char magic[4];                       // CRASH_SPOOL_MAGIC
+        uint32_t length;                     // Bytes of text after the header
+        uint64_t sequence;                   // Nonzero until crashd took the capture
+        uint64_t realtime_ns;                // Wall clock of the capture
+        uint64_t pid;                        // Faulting process, 0 for a kernel panic
+        char component[CRASH_COMPONENT_LEN]; // Process name, or "kernel"
+    } crash_spool_header_t;
+
+    // Classify the fault the next core dump is written for
+    void crash_record_fault(crash_fault_class_t classification, uint64_t address, uint64_t pid,
+                            const char *detail);
+    const char *crash_fault_class_name(crash_fault_class_t classification);
+
+    void save_core_dump(const char *file, int line, const char *function, const char *fmt);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_PANIC_H
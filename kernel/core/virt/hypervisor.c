@@ -0,0 +1,764 @@
+/*
+ * Orion Operating System - Hypervisor
+ *
+ * VMs, their memory and the vCPU run loop, on top of the backend the
+ * architecture registers. Guest memory is memory of the VMM: every page
+ * of a region is populated, locked and given an extra reference for the
+ * second-level tables, so that the VMM unmapping it cannot free it under
+ * the guest. Pages only go back once no vCPU may still hold a translation
+ * of them: removing memory bumps the VM's TLB generation, interrupts the
+ * vCPUs in guest mode and waits for them to leave it, and each vCPU
+ * flushes before entering with a newer generation or on another CPU.
+ *
+ * VMs are reference counted by the calls using them, so that destroying
+ * one while its vCPUs run only stops them; the last call out frees it.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#include <orion/kernel.h>
+#include <orion/types.h>
+#include <orion/mm.h>
+#include <orion/scheduler.h>
+#include <orion/smp.h>
+#include "hypervisor.h"
+
+extern void sched_yield(void);
+extern void arch_enable_interrupts(void);
+
+// Pages unmapped per TLB flush when memory is removed
+#define HV_UNMAP_BATCH 512
+
+static const hv_backend_t *g_backend;
+static hv_vm_t g_vms[HV_MAX_VMS];
+static spinlock_t g_hv_lock = SPINLOCK_INIT;
+
+// CPUs the backend was enabled on
+static uint64_t g_enabled_cpus;
+
+int hv_register_backend(const hv_backend_t *backend)
+{
+    if (!backend)
+    {
+        return -OR_EINVAL;
+    }
+    spinlock_lock(&g_hv_lock);
+    int result = g_backend ? -OR_EBUSY : OR_OK;
+    if (result == OR_OK)
+    {
+        g_backend = backend;
+    }
+    spinlock_unlock(&g_hv_lock);
+    if (result == OR_OK)
+    {
+        kinfo("hypervisor: %s backend registered", backend->name);
+    }
+    return result;
+}
+
+// Mask interrupts and make sure the backend is enabled on this CPU. On
+// success the caller unmasks them once done
+static int hv_cpu_begin(void)
+{
+    arch_disable_interrupts();
+    uint64_t bit = 1ULL << arch_get_current_cpu();
+    if (!(__atomic_load_n(&g_enabled_cpus, __ATOMIC_ACQUIRE) & bit))
+    {
+        int result = g_backend->cpu_enable();
+        if (result != OR_OK)
+        {
+            arch_enable_interrupts();
+            kerror("hypervisor: cannot enable %s on CPU %u", g_backend->name, arch_get_current_cpu());
+            return result;
+        }
+        __atomic_fetch_or(&g_enabled_cpus, bit, __ATOMIC_RELEASE);
+    }
+    return OR_OK;
+}
+
+static bool user_range_ok(const void *ptr, size_t size)
+{
+    return ptr && mmu_is_valid_addr((uint64_t)ptr) && mmu_is_valid_addr((uint64_t)ptr + size - 1);
+}
+
+// ========================================
+// INTERRUPTS AND TLB
+// ========================================
+
+int hv_irq_next(const hv_vcpu_t *vcpu)
+{
+    for (int word = 3; word >= 0; word--)
+    {
+        uint64_t bits = __atomic_load_n(&vcpu->irq_pending[word], __ATOMIC_ACQUIRE);
+        if (bits)
+        {
+            return word * 64 + 63 - __builtin_clzll(bits);
+        }
+    }
+    return -1;
+}
+
+void hv_irq_ack(hv_vcpu_t *vcpu, uint32_t vector)
+{
+    __atomic_fetch_and(&vcpu->irq_pending[vector / 64], ~(1ULL << (vector % 64)), __ATOMIC_RELEASE);
+}
+
+bool hv_tlb_flush_needed(hv_vcpu_t *vcpu, uint32_t cpu)
+{
+    // Published before the generation is read, so that hv_flush_guest
+    // either sees the vCPU in guest mode or the vCPU sees its generation
+    __atomic_store_n(&vcpu->cpu, (int32_t)cpu, __ATOMIC_SEQ_CST);
+    uint64_t generation = __atomic_load_n(&vcpu->vm->tlb_generation, __ATOMIC_SEQ_CST);
+    bool flush = vcpu->last_cpu != (int32_t)cpu || vcpu->tlb_generation != generation;
+    vcpu->last_cpu = (int32_t)cpu;
+    __atomic_store_n(&vcpu->tlb_generation, generation, __ATOMIC_SEQ_CST);
+    return flush;
+}
+
+// Get a vCPU in guest mode out of it
+static void hv_kick(hv_vcpu_t *vcpu)
+{
+    int32_t cpu = __atomic_load_n(&vcpu->cpu, __ATOMIC_SEQ_CST);
+    if (cpu >= 0 && (uint32_t)cpu != arch_get_current_cpu())
+    {
+        smp_send_ipi((uint32_t)cpu, SMP_IPI_RESCHEDULE);
+    }
+}
+
+// Return once no vCPU can use translations older than now
+static void hv_flush_guest(hv_vm_t *vm)
+{
+    uint64_t generation = __atomic_add_fetch(&vm->tlb_generation, 1, __ATOMIC_SEQ_CST);
+    for (uint32_t i = 0; i < vm->vcpu_count; i++)
+    {
+        hv_kick(vm->vcpus[i]);
+    }
+    for (uint32_t i = 0; i < vm->vcpu_count; i++)
+    {
+        hv_vcpu_t *vcpu = vm->vcpus[i];
+        while (__atomic_load_n(&vcpu->cpu, __ATOMIC_SEQ_CST) >= 0 &&
+               __atomic_load_n(&vcpu->tlb_generation, __ATOMIC_SEQ_CST) < generation)
+        {
+            arch_pause();
+        }
+    }
+}
+
+// ========================================
+// MEMORY
+// ========================================
+
+static bool slots_overlap(const hv_memory_region_t *a, const hv_memory_region_t *b)
+{
+    return a->guest_phys < b->guest_phys + b->size && b->guest_phys < a->guest_phys + a->size;
+}
+
+// Unmap `count` pages of a region from `gpa` and release them, flushing
+// every HV_UNMAP_BATCH pages
+static void region_release(hv_vm_t *vm, uint64_t gpa, uint64_t count)
+{
+    uint64_t *pages = kmalloc(HV_UNMAP_BATCH * sizeof(uint64_t));
+    uint64_t batch = pages ? HV_UNMAP_BATCH : 1;
+    uint64_t single;
+    if (!pages)
+    {
+        pages = &single;
+    }
+
+    uint64_t done = 0;
+    while (done < count)
+    {
+        uint64_t n = count - done < batch ? count - done : batch;
+        for (uint64_t i = 0; i < n; i++)
+        {
+            pages[i] = g_backend->unmap(vm, gpa + (done + i) * PAGE_SIZE);
+        }
+        hv_flush_guest(vm);
+        for (uint64_t i = 0; i < n; i++)
+        {
+            if (pages[i])
+            {
+                pmm_free_page(pages[i]);
+            }
+        }
+        done += n;
+    }
+
+    if (pages != &single)
+    {
+        kfree(pages);
+    }
+}
+
+// Host page backing `vaddr` of the VMM, unshared first if the guest may
+// write it
+static int region_page(vm_space_t *space, uint64_t vaddr, bool write, uint64_t *paddr)
+{
+    uint64_t flags;
+    if (!vmm_query_page(space, vaddr, paddr, &flags))
+    {
+        return -OR_EFAULT;
+    }
+    if (write && (flags & PAGE_FLAG_COW))
+    {
+        int result = vmm_handle_cow_fault(space, vaddr);
+        if (result != OR_OK || !vmm_query_page(space, vaddr, paddr, &flags))
+        {
+            return result != OR_OK ? result : -OR_EFAULT;
+        }
+    }
+    if (write && !(flags & PAGE_FLAG_WRITE))
+    {
+        return -OR_EACCES;
+    }
+    return OR_OK;
+}
+
+static int region_add(hv_vm_t *vm, vm_space_t *space, const hv_memory_region_t *region, int slot)
+{
+    uint64_t count = region->size / PAGE_SIZE;
+    int result = vmm_lock_range(space, region->user_addr, count, true);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    bool write = (region->flags & HV_MEM_WRITE) != 0;
+    uint64_t mapped = 0;
+    for (; mapped < count; mapped++)
+    {
+        uint64_t paddr;
+        result = region_page(space, region->user_addr + mapped * PAGE_SIZE, write, &paddr);
+        if (result == OR_OK)
+        {
+            result = pmm_page_share(paddr);
+        }
+        if (result != OR_OK)
+        {
+            break;
+        }
+        result = g_backend->map(vm, region->guest_phys + mapped * PAGE_SIZE, paddr, region->flags);
+        if (result != OR_OK)
+        {
+            pmm_free_page(paddr);
+            break;
+        }
+    }
+
+    if (result != OR_OK)
+    {
+        region_release(vm, region->guest_phys, mapped);
+        vmm_lock_range(space, region->user_addr, count, false);
+        return result;
+    }
+
+    spinlock_lock(&g_hv_lock);
+    vm->slots[slot] = *region;
+    spinlock_unlock(&g_hv_lock);
+    return OR_OK;
+}
+
+static int set_memory(hv_vm_t *vm, const hv_memory_region_t *region)
+{
+    if (!IS_ALIGNED(region->guest_phys, PAGE_SIZE) || !IS_ALIGNED(region->size, PAGE_SIZE) ||
+        !IS_ALIGNED(region->user_addr, PAGE_SIZE) || (region->flags & ~(HV_MEM_WRITE | HV_MEM_EXEC)) ||
+        region->guest_phys >= HV_GUEST_PHYS_LIMIT || region->size > HV_GUEST_PHYS_LIMIT - region->guest_phys)
+    {
+        return -OR_EINVAL;
+    }
+
+    spinlock_lock(&g_hv_lock);
+    if (vm->memory_busy || !vm->space)
+    {
+        spinlock_unlock(&g_hv_lock);
+        return -OR_EBUSY;
+    }
+    vm_space_t *space = vm->space;
+
+    int slot = -1;
+    if (region->size == 0)
+    {
+        // Removal: take the region out of the table before unmapping it
+        hv_memory_region_t removed = {0};
+        for (int i = 0; i < HV_MAX_SLOTS; i++)
+        {
+            if (vm->slots[i].size && vm->slots[i].guest_phys == region->guest_phys)
+            {
+                removed = vm->slots[i];
+                vm->slots[i].size = 0;
+                slot = i;
+                break;
+            }
+        }
+        if (slot >= 0)
+        {
+            vm->memory_busy = true;
+        }
+        spinlock_unlock(&g_hv_lock);
+        if (slot < 0)
+        {
+            return -OR_ENOENT;
+        }
+
+        region_release(vm, removed.guest_phys, removed.size / PAGE_SIZE);
+        vmm_lock_range(space, removed.user_addr, removed.size / PAGE_SIZE, false);
+        spinlock_lock(&g_hv_lock);
+        vm->memory_busy = false;
+        spinlock_unlock(&g_hv_lock);
+        return OR_OK;
+    }
+
+    for (int i = 0; i < HV_MAX_SLOTS; i++)
+    {
+        if (vm->slots[i].size && slots_overlap(&vm->slots[i], region))
+        {
+            spinlock_unlock(&g_hv_lock);
+            return -OR_EEXIST;
+        }
+        if (!vm->slots[i].size && slot < 0)
+        {
+            slot = i;
+        }
+    }
+    if (slot < 0)
+    {
+        spinlock_unlock(&g_hv_lock);
+        return -OR_ENOSPC;
+    }
+    vm->memory_busy = true;
+    spinlock_unlock(&g_hv_lock);
+
+    int result = region_add(vm, space, region, slot);
+
+    spinlock_lock(&g_hv_lock);
+    vm->memory_busy = false;
+    spinlock_unlock(&g_hv_lock);
+    return result;
+}
+
+// ========================================
+// VMS
+// ========================================
+
+// The VM `id` of `owner`, referenced
+static hv_vm_t *vm_get(uint32_t id, uint64_t owner)
+{
+    if (id >= HV_MAX_VMS)
+    {
+        return NULL;
+    }
+    spinlock_lock(&g_hv_lock);
+    hv_vm_t *vm = &g_vms[id];
+    if (!vm->used || vm->dying || vm->owner != owner)
+    {
+        vm = NULL;
+    }
+    else
+    {
+        vm->refs++;
+    }
+    spinlock_unlock(&g_hv_lock);
+    return vm;
+}
+
+// Nobody uses the VM any more: free everything it holds
+static void vm_free(hv_vm_t *vm)
+{
+    for (uint32_t i = 0; i < vm->vcpu_count; i++)
+    {
+        g_backend->vcpu_destroy(vm->vcpus[i]);
+        kfree(vm->vcpus[i]);
+    }
+    for (int i = 0; i < HV_MAX_SLOTS; i++)
+    {
+        hv_memory_region_t *slot = &vm->slots[i];
+        if (slot->size)
+        {
+            region_release(vm, slot->guest_phys, slot->size / PAGE_SIZE);
+            if (vm->space)
+            {
+                vmm_lock_range(vm->space, slot->user_addr, slot->size / PAGE_SIZE, false);
+            }
+        }
+    }
+    g_backend->vm_destroy(vm);
+    kinfo("hypervisor: VM %u of process %llu destroyed", vm->id, (unsigned long long)vm->owner);
+
+    spinlock_lock(&g_hv_lock);
+    uint32_t id = vm->id;
+    memset(vm, 0, sizeof(*vm));
+    vm->id = id;
+    spinlock_unlock(&g_hv_lock);
+}
+
+static void vm_put(hv_vm_t *vm)
+{
+    spinlock_lock(&g_hv_lock);
+    bool last = --vm->refs == 0;
+    spinlock_unlock(&g_hv_lock);
+    if (last)
+    {
+        vm_free(vm);
+    }
+}
+
+// Stop the VM and drop the reference it was created with. With
+// `owner_gone`, its memory is not unlocked in the VMM being destroyed
+static void vm_destroy(hv_vm_t *vm, bool owner_gone)
+{
+    spinlock_lock(&g_hv_lock);
+    bool first = !vm->dying;
+    vm->dying = true;
+    if (owner_gone)
+    {
+        vm->space = NULL;
+    }
+    spinlock_unlock(&g_hv_lock);
+    if (!first)
+    {
+        return;
+    }
+    for (uint32_t i = 0; i < vm->vcpu_count; i++)
+    {
+        hv_kick(vm->vcpus[i]);
+    }
+    vm_put(vm);
+}
+
+static int64_t vm_create(process_t *caller)
+{
+    spinlock_lock(&g_hv_lock);
+    hv_vm_t *vm = NULL;
+    for (uint32_t i = 0; i < HV_MAX_VMS; i++)
+    {
+        if (!g_vms[i].used)
+        {
+            vm = &g_vms[i];
+            memset(vm, 0, sizeof(*vm));
+            vm->id = i;
+            vm->used = true;
+            vm->refs = 1;
+            vm->owner = caller->pid;
+            vm->space = caller->vm_space;
+            vm->memory_busy = true; // Until the backend set it up
+            break;
+        }
+    }
+    spinlock_unlock(&g_hv_lock);
+    if (!vm)
+    {
+        return -OR_ENOSPC;
+    }
+
+    int result = g_backend->vm_init(vm);
+    spinlock_lock(&g_hv_lock);
+    if (result != OR_OK)
+    {
+        vm->used = false;
+    }
+    vm->memory_busy = false;
+    spinlock_unlock(&g_hv_lock);
+    if (result != OR_OK)
+    {
+        return result;
+    }
+
+    kinfo("hypervisor: VM %u created by process %llu", vm->id, (unsigned long long)caller->pid);
+    return vm->id;
+}
+
+void hv_process_exit(uint64_t pid)
+{
+    for (uint32_t i = 0; i < HV_MAX_VMS; i++)
+    {
+        hv_vm_t *vm = vm_get(i, pid);
+        if (vm)
+        {
+            vm_destroy(vm, true);
+            vm_put(vm);
+        }
+    }
+}
+
+// ========================================
+// VCPUS
+// ========================================
+
+static int64_t vcpu_create(hv_vm_t *vm)
+{
+    hv_vcpu_t *vcpu = kmalloc(sizeof(*vcpu));
+    if (!vcpu)
+    {
+        return -OR_ENOMEM;
+    }
+    memset(vcpu, 0, sizeof(*vcpu));
+    vcpu->vm = vm;
+    vcpu->cpu = -1;
+    vcpu->last_cpu = -1;
+
+    spinlock_lock(&g_hv_lock);
+    if (vm->vcpu_count >= HV_MAX_VCPUS)
+    {
+        spinlock_unlock(&g_hv_lock);
+        kfree(vcpu);
+        return -OR_ENOSPC;
+    }
+    // The index is taken now; the vCPU shows once set up
+    vcpu->index = vm->vcpu_count++;
+    vcpu->busy = true;
+    vm->vcpus[vcpu->index] = vcpu;
+    spinlock_unlock(&g_hv_lock);
+
+    int result = hv_cpu_begin();
+    if (result == OR_OK)
+    {
+        result = g_backend->vcpu_init(vcpu);
+        arch_enable_interrupts();
+    }
+    if (result != OR_OK)
+    {
+        // Left in place, busy for good: later indexes are already handed out
+        kerror("hypervisor: cannot set vCPU %u of VM %u up (%d)", vcpu->index, vm->id, result);
+        return result;
+    }
+
+    __atomic_store_n(&vcpu->busy, false, __ATOMIC_RELEASE);
+    return vcpu->index;
+}
+
+// The vCPU `index`, reserved for the caller
+static hv_vcpu_t *vcpu_claim(hv_vm_t *vm, uint32_t index, int *result)
+{
+    spinlock_lock(&g_hv_lock);
+    hv_vcpu_t *vcpu = index < vm->vcpu_count ? vm->vcpus[index] : NULL;
+    *result = vcpu ? OR_OK : -OR_ENOENT;
+    if (vcpu && vcpu->busy)
+    {
+        *result = -OR_EBUSY;
+        vcpu = NULL;
+    }
+    if (vcpu)
+    {
+        vcpu->busy = true;
+    }
+    spinlock_unlock(&g_hv_lock);
+    return vcpu;
+}
+
+static void vcpu_release(hv_vcpu_t *vcpu)
+{
+    __atomic_store_n(&vcpu->busy, false, __ATOMIC_RELEASE);
+}
+
+static int vcpu_state(hv_vcpu_t *vcpu, hv_vcpu_state_t *user_state, bool set)
+{
+    if (!user_range_ok(user_state, sizeof(*user_state)))
+    {
+        return -OR_EFAULT;
+    }
+
+    hv_vcpu_state_t state;
+    if (set)
+    {
+        memcpy(&state, user_state, sizeof(state));
+    }
+    int result = hv_cpu_begin();
+    if (result != OR_OK)
+    {
+        return result;
+    }
+    if (set)
+    {
+        result = g_backend->set_state(vcpu, &state);
+        // A new state supersedes the exit the VMM did not complete
+        vcpu->complete = false;
+    }
+    else
+    {
+        g_backend->get_state(vcpu, &state);
+    }
+    arch_enable_interrupts();
+
+    if (!set)
+    {
+        memcpy(user_state, &state, sizeof(state));
+    }
+    return result;
+}
+
+static int vcpu_run(hv_vcpu_t *vcpu, hv_exit_t *user_exit)
+{
+    if (!user_range_ok(user_exit, sizeof(*user_exit)))
+    {
+        return -OR_EFAULT;
+    }
+    if (vcpu->complete)
+    {
+        memcpy(&vcpu->completion, user_exit, sizeof(vcpu->completion));
+    }
+
+    process_t *self = scheduler_get_current_process();
+    hv_exit_t exit;
+    memset(&exit, 0, sizeof(exit));
+    uint32_t reason = HV_EXIT_NONE;
+
+    g_backend->vcpu_load(vcpu);
+    while (reason == HV_EXIT_NONE)
+    {
+        if (vcpu->vm->dying || (self && self->pending_signals))
+        {
+            reason = HV_EXIT_INTERRUPTED;
+            break;
+        }
+        if (scheduler_need_resched())
+        {
+            sched_yield();
+        }
+
+        int result = hv_cpu_begin();
+        if (result != OR_OK)
+        {
+            g_backend->vcpu_put(vcpu);
+            return result;
+        }
+        reason = g_backend->run(vcpu, &exit);
+        __atomic_store_n(&vcpu->cpu, -1, __ATOMIC_SEQ_CST);
+        // The host takes the interrupt that made the guest exit, if any
+        arch_enable_interrupts();
+    }
+    g_backend->vcpu_put(vcpu);
+
+    exit.reason = reason;
+    vcpu->exit = exit;
+    vcpu->complete = (reason == HV_EXIT_IO && !(exit.io.flags & HV_IO_STRING)) || reason == HV_EXIT_MSR;
+    memcpy(user_exit, &exit, sizeof(exit));
+    return OR_OK;
+}
+
+static int vcpu_interrupt(hv_vm_t *vm, uint32_t index, uint64_t vector)
+{
+    if (vector > 255)
+    {
+        return -OR_EINVAL;
+    }
+    spinlock_lock(&g_hv_lock);
+    hv_vcpu_t *vcpu = index < vm->vcpu_count ? vm->vcpus[index] : NULL;
+    spinlock_unlock(&g_hv_lock);
+    if (!vcpu)
+    {
+        return -OR_ENOENT;
+    }
+    __atomic_fetch_or(&vcpu->irq_pending[vector / 64], 1ULL << (vector % 64), __ATOMIC_RELEASE);
+    hv_kick(vcpu);
+    return OR_OK;
+}
+
+// ========================================
+// SYSTEM CALL
+// ========================================
+
+int64_t hv_ctl(uint32_t op, uint32_t vm_id, uint32_t vcpu_index, uint64_t arg)
+{
+    process_t *caller = scheduler_get_current_process();
+    if (!caller)
+    {
+        return -OR_EINVAL;
+    }
+    if (!g_backend)
+    {
+        return -OR_ENODEV;
+    }
+
+    switch (op)
+    {
+    case HV_OP_CAPS:
+    {
+        hv_caps_t *user_caps = (hv_caps_t *)arg;
+        if (!user_range_ok(user_caps, sizeof(*user_caps)))
+        {
+            return -OR_EFAULT;
+        }
+        hv_caps_t caps;
+        memset(&caps, 0, sizeof(caps));
+        for (size_t i = 0; i < sizeof(caps.backend) - 1 && g_backend->name[i]; i++)
+        {
+            caps.backend[i] = g_backend->name[i];
+        }
+        caps.max_vcpus = HV_MAX_VCPUS;
+        caps.max_slots = HV_MAX_SLOTS;
+        caps.guest_phys_limit = HV_GUEST_PHYS_LIMIT;
+        memcpy(user_caps, &caps, sizeof(caps));
+        return OR_OK;
+    }
+    case HV_OP_VM_CREATE:
+        return vm_create(caller);
+    default:
+        break;
+    }
+
+    hv_vm_t *vm = vm_get(vm_id, caller->pid);
+    if (!vm)
+    {
+        return -OR_ENOENT;
+    }
+
+    int64_t result;
+    switch (op)
+    {
+    case HV_OP_VM_DESTROY:
+        vm_destroy(vm, false);
+        result = OR_OK;
+        break;
+    case HV_OP_SET_MEMORY:
+    {
+        const hv_memory_region_t *user_region = (const hv_memory_region_t *)arg;
+        if (!user_range_ok(user_region, sizeof(*user_region)))
+        {
+            result = -OR_EFAULT;
+            break;
+        }
+        hv_memory_region_t region;
+        memcpy(&region, user_region, sizeof(region));
+        result = set_memory(vm, &region);
+        break;
+    }
+    case HV_OP_VCPU_CREATE:
+        result = vcpu_create(vm);
+        break;
+    case HV_OP_GET_STATE:
+    case HV_OP_SET_STATE:
+    case HV_OP_RUN:
+    {
+        int claimed;
+        hv_vcpu_t *vcpu = vcpu_claim(vm, vcpu_index, &claimed);
+        if (!vcpu)
+        {
+            result = claimed;
+            break;
+        }
+        if (op == HV_OP_RUN)
+        {
+            result = vcpu_run(vcpu, (hv_exit_t *)arg);
+        }
+        else
+        {
+            result = vcpu_state(vcpu, (hv_vcpu_state_t *)arg, op == HV_OP_SET_STATE);
+        }
+        vcpu_release(vcpu);
+        break;
+    }
+    case HV_OP_INTERRUPT:
+        result = vcpu_interrupt(vm, vcpu_index, arg);
+        break;
+    default:
+        result = -OR_EINVAL;
+        break;
+    }
+
+    vm_put(vm);
+    return result;
+}
@@ -0,0 +1,262 @@
+/*
+ * Orion Operating System - Hypervisor
+ *
+ * Hardware virtualization for a virtual machine monitor running in
+ * userspace, in the manner of KVM without its in-kernel devices. The
+ * kernel does what only it can: entering the guest with VMX or SVM,
+ * second-level paging (EPT or NPT) onto memory of the VMM, injecting
+ * interrupts, CPUID and the MSRs a guest kernel lives on. Every other exit
+ * goes back to the VMM: port I/O, accesses to guest physical addresses
+ * without memory (device MMIO, virtio devices included), other MSRs and
+ * HLT. The VMM emulates the devices and the interrupt controller and asks
+ * for the interrupts to inject.
+ *
+ * A VMM creates a VM, gives it memory with HV_OP_SET_MEMORY, creates its
+ * vCPUs and runs each from a thread of its own with HV_OP_RUN, which
+ * returns on the next exit to handle. Guests see the host processor with
+ * the hypervisor CPUID bit set and without VMX, SVM, XSAVE and AVX, so
+ * their FPU state is the SSE one, swapped with the VMM's around HV_OP_RUN.
+ *
+ * Developed by Jeremy Noverraz (1988-2025)
+ * August 2025, Lausanne, Switzerland
+ *
+ * Copyright (c) 2024-2025 Orion OS Project
+ * License: MIT
+ */
+
+#ifndef ORION_HYPERVISOR_H
+#define ORION_HYPERVISOR_H
+
+#include <orion/types.h>
+
+#ifdef __cplusplus
+extern "C"
+{
+#endif
+
+#define HV_MAX_VMS 16
+#define HV_MAX_VCPUS 16 // Per VM
+#define HV_MAX_SLOTS 32 // Memory regions per VM
+#define HV_GUEST_PHYS_LIMIT (1ULL << 40)
+
+// SYS_HV_CTL operations: hv_ctl(op, vm, vcpu, arg)
+#define HV_OP_CAPS 1        // arg: hv_caps_t *
+#define HV_OP_VM_CREATE 2   // Returns the VM id
+#define HV_OP_VM_DESTROY 3  // vm
+#define HV_OP_SET_MEMORY 4  // vm, arg: const hv_memory_region_t *
+#define HV_OP_VCPU_CREATE 5 // vm, returns the vCPU index
+#define HV_OP_GET_STATE 6   // vm, vcpu, arg: hv_vcpu_state_t *
+#define HV_OP_SET_STATE 7   // vm, vcpu, arg: const hv_vcpu_state_t *
+#define HV_OP_RUN 8         // vm, vcpu, arg: hv_exit_t *, see below
+#define HV_OP_INTERRUPT 9   // vm, vcpu, arg: vector to inject
+
+// hv_memory_region_t flags; regions are always readable
+#define HV_MEM_WRITE (1U << 0)
+#define HV_MEM_EXEC (1U << 1)
+
+    typedef struct hv_caps
+    {
+        char backend[8]; // "vmx" or "svm"
+        uint32_t max_vcpus;
+        uint32_t max_slots;
+        uint64_t guest_phys_limit;
+    } hv_caps_t;
+
+    // Guest physical range backed by memory of the VMM from user_addr.
+    // Regions may not overlap; a size of 0 removes the region starting at
+    // guest_phys. Addresses and size are page aligned
+    typedef struct hv_memory_region
+    {
+        uint64_t guest_phys;
+        uint64_t size;
+        uint64_t user_addr;
+        uint32_t flags;
+        uint32_t reserved;
+    } hv_memory_region_t;
+
+    typedef struct hv_regs
+    {
+        uint64_t rax, rbx, rcx, rdx, rsi, rdi, rbp, rsp;
+        uint64_t r8, r9, r10, r11, r12, r13, r14, r15;
+        uint64_t rip, rflags;
+    } hv_regs_t;
+
+    // Attributes are the access byte of the descriptor in bits 0-7 and its
+    // AVL, L, D/B and G flags in bits 12-15; a segment that is not present
+    // is unusable
+    typedef struct hv_segment
+    {
+        uint64_t base;
+        uint32_t limit;
+        uint16_t selector;
+        uint16_t attributes;
+    } hv_segment_t;
+
+    typedef struct hv_dtable
+    {
+        uint64_t base;
+        uint16_t limit;
+        uint16_t reserved[3];
+    } hv_dtable_t;
+
+    typedef struct hv_vcpu_state
+    {
+        hv_regs_t regs;
+        uint64_t cr0, cr2, cr3, cr4, efer;
+        hv_segment_t cs, ds, es, fs, gs, ss, tr, ldtr;
+        hv_dtable_t gdt, idt;
+        uint64_t star, lstar, cstar, sfmask, kernel_gs_base;
+        uint64_t sysenter_cs, sysenter_esp, sysenter_eip;
+    } hv_vcpu_state_t;
+
+    // Exit reasons
+#define HV_EXIT_NONE 0        // Internal: handled, enter the guest again
+#define HV_EXIT_IO 1          // IN, OUT, INS or OUTS
+#define HV_EXIT_MMIO 2        // Access to guest memory without a region allowing it
+#define HV_EXIT_MSR 3         // RDMSR or WRMSR of an MSR the kernel does not keep
+#define HV_EXIT_HLT 4         // HLT with no interrupt to inject; already stepped over
+#define HV_EXIT_INTERRUPTED 5 // A signal is pending; run again once it is handled
+#define HV_EXIT_SHUTDOWN 6    // Triple fault
+#define HV_EXIT_FAIL_ENTRY 7  // The processor rejected the guest state; code tells why
+#define HV_EXIT_UNKNOWN 8     // Exit the kernel does not handle; code is the hardware reason
+
+#define HV_IO_WRITE (1U << 0)
+#define HV_IO_STRING (1U << 1)
+#define HV_IO_REP (1U << 2)
+
+#define HV_ACCESS_READ (1U << 0)
+#define HV_ACCESS_WRITE (1U << 1)
+#define HV_ACCESS_EXEC (1U << 2)
+#define HV_ACCESS_LINEAR (1U << 3) // gla is valid
+
+#define HV_MSR_WRITE (1U << 0)
+#define HV_MSR_FAULT (1U << 1) // Set by the VMM: raise #GP instead
+
+    // Filled by HV_OP_RUN. An IN or OUT (not string) and an MSR access are
+    // completed by the next HV_OP_RUN of the vCPU from the same structure:
+    // io.data is loaded into AL/AX/EAX for an IN, msr.value into EDX:EAX
+    // for a read, and the instruction is stepped over. String I/O and MMIO
+    // are emulated by the VMM, which then updates the state itself
+    typedef struct hv_exit
+    {
+        uint32_t reason;
+        uint32_t reserved;
+        union
+        {
+            struct
+            {
+                uint16_t port;
+                uint8_t size; // 1, 2 or 4
+                uint8_t flags;
+                uint32_t reserved;
+                uint64_t data;
+            } io;
+            struct
+            {
+                uint64_t gpa;
+                uint64_t gla;
+                uint32_t access;
+                uint32_t reserved;
+            } mmio;
+            struct
+            {
+                uint32_t index;
+                uint32_t flags;
+                uint64_t value;
+            } msr;
+            struct
+            {
+                uint64_t code;
+                uint64_t qualification;
+            } hw;
+        };
+    } hv_exit_t;
+
+    // ========================================
+    // KERNEL SIDE
+    // ========================================
+
+    struct hv_vm;
+
+    typedef struct hv_vcpu
+    {
+        struct hv_vm *vm;
+        uint32_t index;
+        bool busy;                 // A thread runs it or accesses its state
+        int32_t cpu;               // CPU it is in guest mode on, -1 outside
+        int32_t last_cpu;          // CPU of its last entry, -1 before the first
+        uint64_t tlb_generation;   // VM generation its translations date from
+        uint64_t irq_pending[4];   // Vectors waiting to be injected
+        hv_exit_t exit;            // Last exit returned to the VMM
+        bool complete;             // exit awaits completion by the next run
+        hv_exit_t completion;      // What the VMM completed it with
+        void *backend;
+    } hv_vcpu_t;
+
+    typedef struct hv_vm
+    {
+        bool used;
+        bool dying;
+        uint32_t id;
+        uint32_t refs;
+        uint64_t owner;   // PID of the VMM
+        void *space;      // Its vm_space_t, NULL once it exits
+        bool memory_busy; // An HV_OP_SET_MEMORY is running
+        hv_memory_region_t slots[HV_MAX_SLOTS];
+        hv_vcpu_t *vcpus[HV_MAX_VCPUS];
+        uint32_t vcpu_count;
+        uint64_t tlb_generation; // Bumped when guest memory goes away
+        void *backend;
+    } hv_vm_t;
+
+    // Hardware side, provided by the architecture. Calls marked masked
+    // run with interrupts masked on a CPU where cpu_enable succeeded
+    typedef struct hv_backend
+    {
+        const char *name;
+        int (*cpu_enable)(void); // Masked, once per CPU
+        int (*vm_init)(hv_vm_t *vm);
+        void (*vm_destroy)(hv_vm_t *vm);
+        // Map one guest page with HV_MEM_* flags
+        int (*map)(hv_vm_t *vm, uint64_t gpa, uint64_t hpa, uint32_t flags);
+        // Unmap a guest page, returning the host page it mapped or 0
+        uint64_t (*unmap)(hv_vm_t *vm, uint64_t gpa);
+        int (*vcpu_init)(hv_vcpu_t *vcpu); // Masked
+        void (*vcpu_destroy)(hv_vcpu_t *vcpu);
+        void (*get_state)(hv_vcpu_t *vcpu, hv_vcpu_state_t *state);       // Masked
+        int (*set_state)(hv_vcpu_t *vcpu, const hv_vcpu_state_t *state); // Masked
+        // Around HV_OP_RUN, interrupts enabled: swap the FPU state
+        void (*vcpu_load)(hv_vcpu_t *vcpu);
+        void (*vcpu_put)(hv_vcpu_t *vcpu);
+        // Enter the guest once and handle the exit, returning HV_EXIT_NONE
+        // to enter again or the reason to return to the VMM. Masked
+        uint32_t (*run)(hv_vcpu_t *vcpu, hv_exit_t *exit);
+    } hv_backend_t;
+
+    /**
+     * Register the virtualization backend; only one may be registered
+     *
+     * @return 0 on success, -OR_EBUSY if one is already registered
+     */
+    int hv_register_backend(const hv_backend_t *backend);
+
+    // SYS_HV_CTL
+    int64_t hv_ctl(uint32_t op, uint32_t vm, uint32_t vcpu, uint64_t arg);
+
+    // Destroy the VMs of a process that is being destroyed
+    void hv_process_exit(uint64_t pid);
+
+    // For backends: highest vector waiting for injection, -1 if none, and
+    // its removal once injected
+    int hv_irq_next(const hv_vcpu_t *vcpu);
+    void hv_irq_ack(hv_vcpu_t *vcpu, uint32_t vector);
+
+    // For backends: whether the translations of a vCPU about to enter on
+    // `cpu` must be flushed; records the entry
+    bool hv_tlb_flush_needed(hv_vcpu_t *vcpu, uint32_t cpu);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ORION_HYPERVISOR_H